@@ -18,18 +18,60 @@
 //!
 //! Command-line interface for interacting with the DotDB document database.
 
+use anyhow::Context;
+use chrono::DateTime;
 use clap::{Parser, Subcommand};
-use dotdb_core::document::{DocumentId, create_persistent_collection_manager};
+use dotdb_core::compaction::scheduler::{COMPACTION_STATUS_FILE, CompactionSchedulerStats};
+use dotdb_core::compaction::strategy::DeadSpaceStrategy;
+use dotdb_core::document::{
+    AggregationSpec, CollectionManager, CompressionCodec, CopyOptions, DocumentId, IndexCopy, ProjectedDocument, ProjectedValue, Projection, ScanOptions, create_in_memory_collection_manager,
+    create_in_memory_collection_manager_with_budget, create_persistent_collection_manager, export_snapshot, import_snapshot,
+};
+use dotdb_core::statistics::{
+    COST_PROFILE_FILE, CalibrationConfig, CostProfile, IndexAdvisorConfig, STATISTICS_FILE, StatisticsCollector, StatisticsConfig, TableFreshness, calibrate, load_cost_profile, save_cost_profile,
+};
+use dotdb_core::storage_engine::{
+    ArchiveCompression, EncryptionStatus, FileFormat, LOCKS_STATUS_FILE, LogEntry, LogSequenceNumber, MASTER_KEY_ENV, MASTER_KEY_FILE_ENV, MasterKey, RecordType, RecoveryReport, RecoveryTarget,
+    StorageConfig, StorageError, StorageResult, VACUUM_REQUESTS_FILE, VACUUM_STATUS_FILE, VacuumStatus, WaitForGraphSnapshot, WalArchiveConfig, WalConfig, WriteAheadLog, request_collection_vacuum,
+};
+use output::{CliError, CorruptPageReport, EncryptionKeyReport, EncryptionReport, FoundDocument, ListedDocument, Output, OutputFormat, ReencryptionReport};
 use serde_json::Value;
+use std::cell::RefCell;
 use std::path::PathBuf;
 use std::process;
+use std::sync::Arc;
+use std::time::Duration;
 use tracing::{error, info};
 
+mod output;
+mod shell;
+mod tx;
+
 #[derive(Parser)]
 #[command(name = "dotdb")]
 #[command(about = "DotDB - Document Database CLI")]
 #[command(version = "0.1.0")]
 struct Cli {
+    /// Namespace the command's collections live in
+    #[arg(long, global = true, default_value = "default")]
+    namespace: String,
+
+    /// Print results as text or as one JSON document; errors are reported in the same format
+    #[arg(long, global = true, value_enum, default_value = "text")]
+    output: OutputFormat,
+
+    /// Keep documents in memory instead of the data directory; nothing survives the process, which suits the shell and tx scripts
+    #[arg(long, global = true)]
+    in_memory: bool,
+
+    /// Refuse writes once the in-memory documents would take more than this many bytes
+    #[arg(long, global = true, value_name = "BYTES", requires = "in_memory")]
+    memory_budget: Option<usize>,
+
+    /// Import a snapshot written by `export` into the in-memory database before running the command
+    #[arg(long, global = true, value_name = "FILE", requires = "in_memory")]
+    load: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -56,6 +98,9 @@ enum Commands {
         collection: String,
         /// Document ID
         id: String,
+        /// Read the document as it was at this RFC 3339 time, e.g. 2025-06-01T12:00:00Z
+        #[arg(long, value_name = "TIME")]
+        as_of: Option<String>,
     },
     /// Update a document by ID
     Update {
@@ -65,6 +110,9 @@ enum Commands {
         id: String,
         /// New JSON document content
         json: String,
+        /// Only update the document if it is still at this version, as `get` reported it
+        #[arg(long, value_name = "VERSION")]
+        if_version: Option<u64>,
     },
     /// Delete a document by ID
     Delete {
@@ -72,11 +120,26 @@ enum Commands {
         collection: String,
         /// Document ID
         id: String,
+        /// Only delete the document if it is still at this version, as `get` reported it
+        #[arg(long, value_name = "VERSION")]
+        if_version: Option<u64>,
     },
-    /// List all document IDs in a collection
+    /// List the document IDs in a collection
     List {
         /// Collection name
         collection: String,
+        /// Order by this path, e.g. age or address.city; documents are ordered by ID otherwise
+        #[arg(long, value_name = "PATH")]
+        sort: Option<String>,
+        /// Sort in descending order
+        #[arg(long)]
+        desc: bool,
+        /// Only list this many documents
+        #[arg(long)]
+        limit: Option<usize>,
+        /// Skip this many documents first
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
     },
     /// List all collections
     Collections,
@@ -94,6 +157,9 @@ enum Commands {
     Count {
         /// Collection name
         collection: String,
+        /// Count the documents the collection held at this RFC 3339 time
+        #[arg(long, value_name = "TIME")]
+        as_of: Option<String>,
     },
     /// Find documents by field value
     Find {
@@ -103,52 +169,584 @@ enum Commands {
         field: String,
         /// Field value (JSON)
         value: String,
+        /// Only return these comma-separated paths, e.g. name,status,address.city
+        #[arg(long, value_name = "PATHS")]
+        project: Option<String>,
+        /// Search the collection as it was at this RFC 3339 time
+        #[arg(long, value_name = "TIME")]
+        as_of: Option<String>,
+    },
+    /// Group documents by fields and time windows and aggregate each group
+    ///
+    /// The spec is JSON, e.g. '{"group_by": ["region"], "window": {"field": "ts",
+    /// "size": {"minutes": 5}}, "aggregates": [{"function": "count"}, {"function":
+    /// "avg", "field": "latency"}]}'.
+    Aggregate {
+        /// Collection name
+        collection: String,
+        /// Aggregation spec (JSON)
+        spec: String,
+    },
+    /// Start an interactive shell
+    Shell,
+    /// Run document operations from a file or standard input as one transaction
+    ///
+    /// One operation per line: put <collection> <json>, get <collection> <id>,
+    /// update <collection> <id> <json> or delete <collection> <id>. Nothing is
+    /// written unless every operation succeeds.
+    Tx {
+        /// Isolation level to run the transaction at
+        #[arg(long, value_enum, default_value = "read-committed")]
+        isolation: tx::IsolationArg,
+        /// File containing the operations (defaults to standard input)
+        #[arg(long, short = 'f')]
+        file: Option<PathBuf>,
+    },
+    /// Manage namespaces
+    Namespace {
+        #[command(subcommand)]
+        command: NamespaceCommands,
+    },
+    /// Configure collections
+    Collection {
+        #[command(subcommand)]
+        command: CollectionCommands,
+    },
+    /// Manage the JSON Schemas documents in a collection must match
+    Schema {
+        #[command(subcommand)]
+        command: SchemaCommands,
+    },
+    /// Recommend indexes from the field accesses seen by earlier commands
+    Advisor {
+        /// Only count accesses and writes from the last N seconds (defaults to 24 hours)
+        #[arg(long)]
+        window_secs: Option<u64>,
+        /// Print the recommendations as JSON (same as `--output json`)
+        #[arg(long)]
+        json: bool,
+    },
+    /// Write a WAL checkpoint, storing the page store in the archive as the base recovery starts on
+    Checkpoint {
+        /// WAL directory (defaults to <data dir>/wal)
+        #[arg(long)]
+        wal_dir: Option<PathBuf>,
+        /// WAL archive directory (defaults to <data dir>/wal_archive)
+        #[arg(long)]
+        archive_dir: Option<PathBuf>,
+        /// Page store to snapshot (defaults to <data dir>/storage.db)
+        #[arg(long)]
+        data_file: Option<PathBuf>,
+    },
+    /// Restore the page store to a point in time by replaying archived WAL segments
+    ///
+    /// The page store is rebuilt in a separate file from the base snapshot of the checkpoint
+    /// replay starts from and only replaces the live one once every recovered page is written.
+    Recover {
+        /// Replay transactions committed at or before this Unix time (seconds)
+        #[arg(long, conflicts_with = "to_lsn", required_unless_present = "to_lsn")]
+        to_timestamp: Option<u64>,
+        /// Replay records up to and including this LSN, written as FILE_ID/OFFSET
+        #[arg(long)]
+        to_lsn: Option<String>,
+        /// WAL directory (defaults to <data dir>/wal)
+        #[arg(long)]
+        wal_dir: Option<PathBuf>,
+        /// WAL archive directory (defaults to <data dir>/wal_archive)
+        #[arg(long)]
+        archive_dir: Option<PathBuf>,
+        /// Page store to replace with the recovered one (defaults to <data dir>/storage.db)
+        #[arg(long)]
+        data_file: Option<PathBuf>,
+    },
+    /// Scan every page of the page store, verify checksums and quarantine corrupt pages
+    Verify {
+        /// Page store to verify (defaults to <data dir>/storage.db)
+        #[arg(long)]
+        data_file: Option<PathBuf>,
+    },
+    /// Encrypt the page store at rest and rotate its keys
+    ///
+    /// The master key is read from DOTDB_MASTER_KEY (64 hex digits) or from the file named by
+    /// DOTDB_MASTER_KEY_FILE.
+    Encryption {
+        #[command(subcommand)]
+        command: EncryptionCommands,
+    },
+    /// Inspect background compaction
+    Compaction {
+        #[command(subcommand)]
+        command: CompactionCommands,
+    },
+    /// Show the lock wait-for graph published by the deadlock detector
+    Locks {
+        /// Print the raw graph as JSON (same as `--output json`)
+        #[arg(long)]
+        json: bool,
+    },
+    /// Inspect or request garbage collection of old MVCC versions
+    Vacuum {
+        #[command(subcommand)]
+        command: VacuumCommands,
+    },
+    /// Inspect query planner statistics
+    Stats {
+        #[command(subcommand)]
+        command: StatsCommands,
+    },
+    /// Write every namespace, collection and document to a snapshot (JSON lines)
+    Export {
+        /// Snapshot file to write (defaults to standard output)
+        #[arg(long, short = 'o')]
+        file: Option<PathBuf>,
+    },
+    /// Load a snapshot written by `export`, replacing documents with the same ID
+    Import {
+        /// Snapshot file to read
+        file: PathBuf,
+    },
+    /// Benchmark the storage device and save a cost profile for the query planner
+    Calibrate {
+        /// Print the saved profile as JSON (same as `--output json`)
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum NamespaceCommands {
+    /// Create a namespace
+    Create {
+        /// Namespace name (ASCII letters, digits, `_` and `-`)
+        name: String,
+    },
+    /// List all namespaces
+    List,
+    /// Drop a namespace
+    Drop {
+        /// Namespace name
+        name: String,
+        /// Also delete the namespace's collections and documents
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum CollectionCommands {
+    /// Compress the documents written to a collection from now on
+    SetCompression {
+        /// Collection name
+        collection: String,
+        /// none, lz4, zstd or zstd:<level> with a level from 1 to 22
+        codec: String,
+        /// Also store the collection's existing documents again under the new codec
+        #[arg(long)]
+        rewrite: bool,
+    },
+    /// Rename a collection with its documents, history, schema, codec and covering indexes
+    Rename {
+        /// Current collection name
+        from: String,
+        /// New collection name; must not exist yet
+        to: String,
+    },
+    /// Copy a collection's documents into a new collection as of a snapshot of the source
+    Copy {
+        /// Source collection name
+        from: String,
+        /// Destination collection name; must not exist yet
+        to: String,
+        /// Copy only documents whose FIELD equals VALUE (JSON), e.g. --where status '"active"'
+        #[arg(long = "where", value_names = ["FIELD", "VALUE"], num_args = 2)]
+        filter: Option<Vec<String>>,
+        /// What to do with the source's covering indexes
+        #[arg(long, value_enum, default_value = "skip")]
+        indexes: IndexCopyArg,
+    },
+}
+
+/// Covering index handling accepted by `collection copy --indexes`
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum IndexCopyArg {
+    /// Leave the destination without covering indexes
+    Skip,
+    /// Create the indexes first and keep them current while documents are copied
+    Copy,
+    /// Build the indexes once every document is copied
+    Rebuild,
+}
+
+impl From<IndexCopyArg> for IndexCopy {
+    fn from(arg: IndexCopyArg) -> Self {
+        match arg {
+            IndexCopyArg::Skip => IndexCopy::Skip,
+            IndexCopyArg::Copy => IndexCopy::Copy,
+            IndexCopyArg::Rebuild => IndexCopy::Rebuild,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum SchemaCommands {
+    /// Require documents written to a collection to match a JSON Schema
+    Set {
+        /// Collection name
+        collection: String,
+        /// JSON Schema (draft 2020-12 subset)
+        schema: String,
+        /// Also check the documents already in the collection and report the ones that do not match
+        #[arg(long)]
+        validate_existing: bool,
+    },
+    /// Print a collection's schema
+    Get {
+        /// Collection name
+        collection: String,
+    },
+    /// Check every document in a collection against its schema
+    Check {
+        /// Collection name
+        collection: String,
+        /// Print the report as JSON (same as `--output json`)
+        #[arg(long)]
+        json: bool,
+    },
+    /// Stop enforcing a collection's schema
+    Remove {
+        /// Collection name
+        collection: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum StatsCommands {
+    /// Show how fresh a collection's histograms and cardinality sketches are
+    Show {
+        /// Collection name
+        collection: String,
+        /// Print the raw freshness report as JSON (same as `--output json`)
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show a collection's logical size, on-disk size and space amplification
+    Space {
+        /// Collection name
+        collection: String,
+        /// Print the raw usage as JSON (same as `--output json`)
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum CompactionCommands {
+    /// Show progress and throughput of the background compaction scheduler
+    Status {
+        /// Print the raw stats as JSON (same as `--output json`)
+        #[arg(long)]
+        json: bool,
+    },
+    /// Drop expired tombstones and rewrite the segments holding the most dead space
+    Run {
+        /// Smallest fraction of dead bytes a segment needs to be rewritten
+        #[arg(long, default_value_t = DeadSpaceStrategy::default().dead_ratio_threshold)]
+        dead_ratio: f64,
     },
 }
 
+#[derive(Subcommand)]
+enum EncryptionCommands {
+    /// Show whether the page store is encrypted, its keys and re-encryption progress; needs no key
+    Status {
+        /// Page store to inspect (defaults to <data dir>/storage.db)
+        #[arg(long)]
+        data_file: Option<PathBuf>,
+        /// Print the status as JSON (same as `--output json`)
+        #[arg(long)]
+        json: bool,
+    },
+    /// Encrypt an unencrypted page store in place
+    Enable {
+        /// Page store to encrypt (defaults to <data dir>/storage.db)
+        #[arg(long)]
+        data_file: Option<PathBuf>,
+        /// Encrypt every existing page before returning instead of leaving it to the storage engine
+        #[arg(long)]
+        wait: bool,
+    },
+    /// Activate a new data encryption key; pages are re-encrypted with it in the background
+    Rotate {
+        /// Page store to rotate the key of (defaults to <data dir>/storage.db)
+        #[arg(long)]
+        data_file: Option<PathBuf>,
+        /// Re-encrypt every page before returning instead of leaving it to the storage engine
+        #[arg(long)]
+        wait: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum VacuumCommands {
+    /// Show the watermark, retention and reclaimed space of the background vacuum
+    Status {
+        /// Print the raw status as JSON (same as `--output json`)
+        #[arg(long)]
+        json: bool,
+    },
+    /// Queue a full vacuum of a collection for the running storage engine
+    Run {
+        /// Collection to vacuum
+        #[arg(long)]
+        collection: String,
+    },
+}
+
+impl Commands {
+    /// Whether the command was given its own `--json` flag, kept as a shorthand for `--output json`
+    fn json_requested(&self) -> bool {
+        match self {
+            Self::Advisor { json, .. }
+            | Self::Locks { json }
+            | Self::Calibrate { json }
+            | Self::Schema {
+                command: SchemaCommands::Check { json, .. },
+            }
+            | Self::Stats {
+                command: StatsCommands::Show { json, .. } | StatsCommands::Space { json, .. },
+            }
+            | Self::Compaction {
+                command: CompactionCommands::Status { json },
+            }
+            | Self::Vacuum {
+                command: VacuumCommands::Status { json },
+            }
+            | Self::Encryption {
+                command: EncryptionCommands::Status { json, .. },
+            } => *json,
+            _ => false,
+        }
+    }
+}
+
 fn main() {
-    // Initialize tracing
-    tracing_subscriber::fmt::init();
+    // Logs go to standard error so standard output only carries results
+    tracing_subscriber::fmt().with_writer(std::io::stderr).init();
 
     let cli = Cli::parse();
+    let format = if cli.command.json_requested() { OutputFormat::Json } else { cli.output };
 
+    let code = output::finish(format, run(cli, format));
+    process::exit(code as i32);
+}
+
+fn run(cli: Cli, format: OutputFormat) -> anyhow::Result<Output> {
     // For now, use default data directory since we can't easily parse global args with subcommands
     let data_dir = get_data_directory(None);
 
     // Ensure data directory exists
-    if let Err(e) = std::fs::create_dir_all(&data_dir) {
-        error!("Failed to create data directory {}: {}", data_dir.display(), e);
-        process::exit(1);
+    std::fs::create_dir_all(&data_dir).with_context(|| format!("Failed to create data directory {}", data_dir.display()))?;
+
+    // Recovery works on the page store directly and must not open the document database
+    if let Commands::Recover {
+        to_timestamp,
+        to_lsn,
+        wal_dir,
+        archive_dir,
+        data_file,
+    } = cli.command
+    {
+        let wal_dir = wal_dir.unwrap_or_else(|| data_dir.join("wal"));
+        let archive_dir = archive_dir.unwrap_or_else(|| data_dir.join("wal_archive"));
+        let data_file = data_file.unwrap_or_else(|| data_dir.join("storage.db"));
+        return handle_recover(to_timestamp, to_lsn.as_deref(), wal_dir, archive_dir, data_file).context("Recovery failed");
     }
 
-    // Create collection manager with persistent storage
-    let manager = match create_persistent_collection_manager(&data_dir, None) {
-        Ok(manager) => manager,
-        Err(e) => {
-            error!("Failed to create collection manager: {}", e);
-            process::exit(1);
-        }
+    // Checkpoints snapshot the page store file, so the document database must not have it open
+    if let Commands::Checkpoint { wal_dir, archive_dir, data_file } = cli.command {
+        let wal_dir = wal_dir.unwrap_or_else(|| data_dir.join("wal"));
+        let archive_dir = archive_dir.unwrap_or_else(|| data_dir.join("wal_archive"));
+        let data_file = data_file.unwrap_or_else(|| data_dir.join("storage.db"));
+        return handle_checkpoint(wal_dir, archive_dir, data_file).context("Checkpoint failed");
+    }
+
+    // Verification reads raw pages and must not go through the document database
+    if let Commands::Verify { data_file } = cli.command {
+        let data_file = data_file.unwrap_or_else(|| data_dir.join("storage.db"));
+        return handle_verify(&data_file).context("Verification failed");
+    }
+
+    // Encryption works on the page store directly; its status only reads the file header
+    if let Commands::Encryption { command } = cli.command {
+        return handle_encryption(command, &data_dir).context("Encryption command failed");
+    }
+
+    // Compaction status is published by the running storage engine and only needs the data directory
+    if let Commands::Compaction {
+        command: CompactionCommands::Status { .. },
+    } = cli.command
+    {
+        return handle_compaction_status(&data_dir.join(COMPACTION_STATUS_FILE)).context("Failed to read compaction status");
+    }
+
+    // The wait-for graph is published by the running storage engine as well
+    if let Commands::Locks { .. } = cli.command {
+        return handle_locks(&data_dir.join(LOCKS_STATUS_FILE)).context("Failed to read lock status");
+    }
+
+    // The vacuum publishes its status and picks up requests through files in the data directory
+    if let Commands::Vacuum { command } = &cli.command {
+        let result = match command {
+            VacuumCommands::Status { .. } => handle_vacuum_status(&data_dir.join(VACUUM_STATUS_FILE)),
+            VacuumCommands::Run { collection } => {
+                // Collections outside the default namespace are tracked under their qualified name
+                let collection = if cli.namespace == "default" {
+                    collection.clone()
+                } else {
+                    format!("{}.{}", cli.namespace, collection)
+                };
+                handle_vacuum_run(&data_dir, &collection)
+            }
+        };
+        return result.context("Vacuum command failed");
+    }
+
+    // Statistics are persisted by the collector and only need the data directory
+    if let Commands::Stats {
+        command: StatsCommands::Show { collection, .. },
+    } = &cli.command
+    {
+        // Collections outside the default namespace are tracked under their qualified name
+        let table = if cli.namespace == "default" {
+            collection.clone()
+        } else {
+            format!("{}.{}", cli.namespace, collection)
+        };
+        return handle_stats_show(&data_dir.join(STATISTICS_FILE), &table).context("Failed to read statistics");
+    }
+
+    // Calibration benchmarks the device under the data directory and stores the profile beside the statistics
+    if let Commands::Calibrate { .. } = cli.command {
+        return handle_calibrate(&data_dir).context("Calibration failed");
+    }
+
+    // Create collection manager with persistent storage, or in memory when asked; an in-memory run keeps no advisor state either
+    let mut advisor_config = IndexAdvisorConfig {
+        persistence_path: (!cli.in_memory).then(|| data_dir.join(INDEX_ADVISOR_FILE)),
+        ..IndexAdvisorConfig::default()
+    };
+    if let Commands::Advisor { window_secs: Some(secs), .. } = cli.command {
+        advisor_config.window = Duration::from_secs(secs);
+    }
+    let manager = match (cli.in_memory, cli.memory_budget) {
+        (false, _) => create_persistent_collection_manager(&data_dir, None),
+        (true, None) => create_in_memory_collection_manager(),
+        (true, Some(budget)) => create_in_memory_collection_manager_with_budget(budget),
     };
+    let root = manager.and_then(|manager| manager.with_index_advisor(advisor_config)).context("Failed to create collection manager")?;
+
+    // Writes are counted in the statistics persisted beside the data, re-analyzing collections that changed a lot
+    let mut statistics = None;
+    let root = if cli.in_memory {
+        root
+    } else {
+        let runtime = tokio::runtime::Runtime::new().context("Failed to start the statistics runtime")?;
+        let config = StatisticsConfig {
+            persistence_path: Some(data_dir.join(STATISTICS_FILE)),
+            ..StatisticsConfig::default()
+        };
+        let collector = Arc::new(runtime.block_on(StatisticsCollector::open(config)).context("Failed to load statistics")?);
+        let (root, recording) = {
+            let _runtime = runtime.enter();
+            root.with_statistics(&collector)
+        };
+        statistics = Some((runtime, collector, recording));
+        root
+    };
+    if let Some(file) = &cli.load {
+        let reader = std::io::BufReader::new(std::fs::File::open(file).with_context(|| format!("Failed to open {}", file.display()))?);
+        import_snapshot(root.storage().as_ref(), reader).with_context(|| format!("Failed to load {}", file.display()))?;
+        info!("Loaded snapshot {} into memory", file.display());
+    }
+    let manager = root.with_namespace(&cli.namespace)?;
 
-    let result = match cli.command {
+    let output = match cli.command {
         Commands::Put { collection, json } => handle_put(&manager, &collection, &json),
-        Commands::Get { collection, id } => handle_get(&manager, &collection, &id),
-        Commands::Update { collection, id, json } => handle_update(&manager, &collection, &id, &json),
-        Commands::Delete { collection, id } => handle_delete(&manager, &collection, &id),
-        Commands::List { collection } => handle_list(&manager, &collection),
+        Commands::Get { collection, id, as_of } => handle_get(&manager, &collection, &id, as_of.as_deref()),
+        Commands::Update { collection, id, json, if_version } => handle_update(&manager, &collection, &id, &json, if_version),
+        Commands::Delete { collection, id, if_version } => handle_delete(&manager, &collection, &id, if_version),
+        Commands::List {
+            collection,
+            sort,
+            desc,
+            limit,
+            offset,
+        } => {
+            let options = ScanOptions {
+                sort_field: sort,
+                descending: desc,
+                limit,
+                offset,
+            };
+            handle_list(&manager, &collection, &options)
+        }
         Commands::Collections => handle_list_collections(&manager),
         Commands::CreateCollection { collection } => handle_create_collection(&manager, &collection),
         Commands::DeleteCollection { collection } => handle_delete_collection(&manager, &collection),
-        Commands::Count { collection } => handle_count(&manager, &collection),
-        Commands::Find { collection, field, value } => handle_find(&manager, &collection, &field, &value),
-    };
+        Commands::Count { collection, as_of } => handle_count(&manager, &collection, as_of.as_deref()),
+        Commands::Find {
+            collection,
+            field,
+            value,
+            project,
+            as_of,
+        } => handle_find(&manager, &collection, &field, &value, project.as_deref(), as_of.as_deref()),
+        Commands::Aggregate { collection, spec } => handle_aggregate(&manager, &collection, &spec),
+        Commands::Shell => shell::run(&manager, data_dir.join("shell_history"), format == OutputFormat::Json).map(|()| Output::None),
+        Commands::Tx { isolation, file } => tx::run(&manager, isolation, file.as_deref()),
+        Commands::Advisor { .. } => Ok(handle_advisor(&manager)),
+        Commands::Namespace { command } => handle_namespace(&root, command, &cli.namespace),
+        Commands::Collection { command } => handle_collection(&manager, command),
+        Commands::Schema { command } => handle_schema(&manager, command),
+        Commands::Export { file } => handle_export(&root, file),
+        Commands::Import { file } => handle_import(&root, file),
+        Commands::Recover { .. } => unreachable!("recover is handled before the collection manager is opened"),
+        Commands::Checkpoint { .. } => unreachable!("checkpoint is handled before the collection manager is opened"),
+        Commands::Verify { .. } => unreachable!("verify is handled before the collection manager is opened"),
+        Commands::Encryption { .. } => unreachable!("encryption commands are handled before the collection manager is opened"),
+        Commands::Compaction {
+            command: CompactionCommands::Run { dead_ratio },
+        } => handle_compaction_run(&manager, dead_ratio),
+        Commands::Compaction { .. } => unreachable!("compaction status is handled before the collection manager is opened"),
+        Commands::Locks { .. } => unreachable!("the locks command is handled before the collection manager is opened"),
+        Commands::Vacuum { .. } => unreachable!("vacuum commands are handled before the collection manager is opened"),
+        Commands::Stats {
+            command: StatsCommands::Space { collection, .. },
+        } => handle_stats_space(&manager, &collection),
+        Commands::Stats { .. } => unreachable!("stats show is handled before the collection manager is opened"),
+        Commands::Calibrate { .. } => unreachable!("calibration is handled before the collection manager is opened"),
+    }?;
 
-    if let Err(e) = result {
-        error!("Command failed: {}", e);
-        process::exit(1);
+    // Flush so the index advisor's window carries over to the next invocation
+    manager.flush()?;
+
+    // Once every handle is gone the recorder has seen all the writes, and the statistics can be saved
+    drop((manager, root));
+    if let Some((runtime, collector, recording)) = statistics {
+        runtime
+            .block_on(async {
+                recording.await?;
+                collector.persist().await?;
+                anyhow::Ok(())
+            })
+            .context("Failed to save statistics")?;
     }
+    Ok(output)
 }
 
+/// File in the data directory holding the index advisor's observed window
+const INDEX_ADVISOR_FILE: &str = "index_advisor.json";
+
 /// Get the data directory for persistent storage with XDG compliance
 fn get_data_directory(custom_dir: Option<PathBuf>) -> PathBuf {
     if let Some(dir) = custom_dir {
@@ -166,133 +764,674 @@ fn get_data_directory(custom_dir: Option<PathBuf>) -> PathBuf {
     }
 }
 
-fn handle_put(manager: &dotdb_core::document::CollectionManager, collection: &str, json: &str) -> anyhow::Result<()> {
+fn handle_put(manager: &dotdb_core::document::CollectionManager, collection: &str, json: &str) -> anyhow::Result<Output> {
     // Validate JSON
     let _: Value = serde_json::from_str(json)?;
 
     let id = manager.insert_json(collection, json)?;
-    println!("Document inserted with ID: {id}");
     info!("Inserted document {} into collection {}", id, collection);
-    Ok(())
+    Ok(Output::Inserted {
+        collection: collection.to_string(),
+        id,
+    })
 }
 
-fn handle_get(manager: &dotdb_core::document::CollectionManager, collection: &str, id_str: &str) -> anyhow::Result<()> {
+fn handle_get(manager: &dotdb_core::document::CollectionManager, collection: &str, id_str: &str, as_of: Option<&str>) -> anyhow::Result<Output> {
     let id = DocumentId::from_string(id_str)?;
 
-    match manager.get_json(collection, &id)? {
-        Some(json) => {
-            println!("{json}");
-            info!("Retrieved document {} from collection {}", id, collection);
-        }
-        None => {
-            println!("Document not found");
-            info!("Document {} not found in collection {}", id, collection);
-        }
-    }
-    Ok(())
+    let document = match as_of {
+        Some(as_of) => manager.get_value_as_of(collection, &id, parse_as_of(as_of)?)?.map(|document| (document, None)),
+        None => manager.get_value_with_version(collection, &id)?.map(|(document, version)| (document, Some(version))),
+    };
+    let Some((document, version)) = document else {
+        info!("Document {} not found in collection {}", id, collection);
+        return Err(CliError::not_found(format!("Document not found: {id}")).into());
+    };
+    info!("Retrieved document {} from collection {}", id, collection);
+    Ok(Output::Document {
+        collection: collection.to_string(),
+        id,
+        document,
+        version,
+        as_of: as_of.map(str::to_string),
+    })
 }
 
-fn handle_update(manager: &dotdb_core::document::CollectionManager, collection: &str, id_str: &str, json: &str) -> anyhow::Result<()> {
+fn handle_update(manager: &dotdb_core::document::CollectionManager, collection: &str, id_str: &str, json: &str, if_version: Option<u64>) -> anyhow::Result<Output> {
     let id = DocumentId::from_string(id_str)?;
 
     // Validate JSON
     let _: Value = serde_json::from_str(json)?;
 
-    manager.update_json(collection, &id, json)?;
-    println!("Document updated: {id}");
+    let version = match if_version {
+        Some(expected) => Some(manager.update_json_if_version(collection, &id, json, expected)?),
+        None => {
+            manager.update_json(collection, &id, json)?;
+            None
+        }
+    };
     info!("Updated document {} in collection {}", id, collection);
-    Ok(())
+    Ok(Output::Updated {
+        collection: collection.to_string(),
+        id,
+        version,
+    })
 }
 
-fn handle_delete(manager: &dotdb_core::document::CollectionManager, collection: &str, id_str: &str) -> anyhow::Result<()> {
+fn handle_delete(manager: &dotdb_core::document::CollectionManager, collection: &str, id_str: &str, if_version: Option<u64>) -> anyhow::Result<Output> {
     let id = DocumentId::from_string(id_str)?;
 
-    let deleted = manager.delete(collection, &id)?;
-    if deleted {
-        println!("Document deleted: {id}");
-        info!("Deleted document {} from collection {}", id, collection);
-    } else {
-        println!("Document not found: {id}");
+    let deleted = match if_version {
+        Some(expected) => manager.delete_if_version(collection, &id, expected)?,
+        None => manager.delete(collection, &id)?,
+    };
+    if !deleted {
         info!("Document {} not found in collection {}", id, collection);
+        return Err(CliError::not_found(format!("Document not found: {id}")).into());
     }
-    Ok(())
+    info!("Deleted document {} from collection {}", id, collection);
+    Ok(Output::Deleted {
+        collection: collection.to_string(),
+        id,
+    })
 }
 
-fn handle_list(manager: &dotdb_core::document::CollectionManager, collection: &str) -> anyhow::Result<()> {
-    let doc_ids = manager.list_document_ids(collection)?;
-    let count = doc_ids.len();
+fn handle_list(manager: &dotdb_core::document::CollectionManager, collection: &str, options: &ScanOptions) -> anyhow::Result<Output> {
+    let sort_field = options.sort_field.as_deref().map(|path| Projection::new(&[path])).transpose()?;
+    let documents: Vec<ListedDocument> = manager
+        .list_documents(collection, options)?
+        .into_iter()
+        .map(|(id, document)| {
+            let sort_value = sort_field.as_ref().map(|projection| match projection.project(&document).pop() {
+                Some(ProjectedValue::Present(value)) => value.to_string(),
+                _ => "(missing)".to_string(),
+            });
+            ListedDocument { id, sort_value, document }
+        })
+        .collect();
 
-    if doc_ids.is_empty() {
-        println!("No documents found in collection '{collection}'");
-    } else {
-        println!("Documents in collection '{collection}':");
-        for id in doc_ids {
-            println!("  {id}");
+    info!("Listed {} documents in collection {}", documents.len(), collection);
+    Ok(Output::Documents {
+        collection: collection.to_string(),
+        sort: options.sort_field.clone(),
+        documents,
+    })
+}
+
+fn handle_list_collections(manager: &dotdb_core::document::CollectionManager) -> anyhow::Result<Output> {
+    let collections = manager.list_collections()?;
+    info!("Listed {} collections", collections.len());
+    Ok(Output::Collections {
+        namespace: manager.namespace().to_string(),
+        collections,
+    })
+}
+
+fn handle_create_collection(manager: &dotdb_core::document::CollectionManager, collection: &str) -> anyhow::Result<Output> {
+    manager.create_collection(collection)?;
+    info!("Created collection {}", collection);
+    Ok(Output::CollectionCreated { collection: collection.to_string() })
+}
+
+fn handle_delete_collection(manager: &dotdb_core::document::CollectionManager, collection: &str) -> anyhow::Result<Output> {
+    if !manager.delete_collection(collection)? {
+        info!("Collection {} not found", collection);
+        return Err(CliError::not_found(format!("Collection not found: {collection}")).into());
+    }
+    info!("Deleted collection {}", collection);
+    Ok(Output::CollectionDeleted { collection: collection.to_string() })
+}
+
+fn handle_count(manager: &dotdb_core::document::CollectionManager, collection: &str, as_of: Option<&str>) -> anyhow::Result<Output> {
+    let count = match as_of {
+        Some(as_of) => manager.count_as_of(collection, parse_as_of(as_of)?)?,
+        None => manager.count(collection)?,
+    };
+    info!("Counted {} documents in collection {}", count, collection);
+    Ok(Output::Count {
+        collection: collection.to_string(),
+        count,
+        as_of: as_of.map(str::to_string),
+    })
+}
+
+fn handle_find(manager: &dotdb_core::document::CollectionManager, collection: &str, field: &str, value_str: &str, project: Option<&str>, as_of: Option<&str>) -> anyhow::Result<Output> {
+    let value: Value = serde_json::from_str(value_str)?;
+
+    let documents: Vec<FoundDocument> = match (project, as_of) {
+        // Covering indexes only hold current values, so past documents are projected here
+        (Some(paths), Some(as_of)) => {
+            let paths: Vec<&str> = paths.split(',').collect();
+            let projection = Projection::new(&paths)?;
+            manager
+                .find_by_field_as_of(collection, field, &value, parse_as_of(as_of)?)?
+                .into_iter()
+                .map(|(id, document)| {
+                    let projected = ProjectedDocument {
+                        id,
+                        fields: projection.paths().map(str::to_string).zip(projection.project(&document)).collect(),
+                    };
+                    FoundDocument {
+                        missing: projected.missing().into_iter().map(str::to_string).collect(),
+                        document: projected.to_value(),
+                        id: projected.id,
+                    }
+                })
+                .collect()
+        }
+        (Some(paths), None) => {
+            let paths: Vec<&str> = paths.split(',').collect();
+            manager
+                .find_by_field_projected(collection, field, &value, &paths)?
+                .into_iter()
+                .map(|projected| FoundDocument {
+                    missing: projected.missing().into_iter().map(str::to_string).collect(),
+                    document: projected.to_value(),
+                    id: projected.id,
+                })
+                .collect()
+        }
+        (None, as_of) => {
+            let documents = match as_of {
+                Some(as_of) => manager.find_by_field_as_of(collection, field, &value, parse_as_of(as_of)?)?,
+                None => manager.find_by_field(collection, field, &value)?,
+            };
+            documents.into_iter().map(|(id, document)| FoundDocument { id, document, missing: Vec::new() }).collect()
+        }
+    };
+
+    info!("Found {} documents in collection {} matching {}={}", documents.len(), collection, field, value);
+    Ok(Output::Found {
+        collection: collection.to_string(),
+        field: field.to_string(),
+        value,
+        documents,
+        as_of: as_of.map(str::to_string),
+    })
+}
+
+fn handle_aggregate(manager: &CollectionManager, collection: &str, spec: &str) -> anyhow::Result<Output> {
+    let spec: AggregationSpec = serde_json::from_str(spec).context("Invalid aggregation spec")?;
+    let result = manager.aggregate(collection, &spec)?;
+    info!("Aggregated {} documents of collection {} into {} groups", result.documents_aggregated, collection, result.groups.len());
+    Ok(Output::Aggregated {
+        collection: collection.to_string(),
+        result,
+    })
+}
+
+fn handle_namespace(manager: &CollectionManager, command: NamespaceCommands, active: &str) -> anyhow::Result<Output> {
+    match command {
+        NamespaceCommands::Create { name } => {
+            let created = manager.create_namespace(&name)?;
+            if created {
+                info!("Created namespace {}", name);
+            }
+            Ok(Output::NamespaceCreated { namespace: name, created })
+        }
+        NamespaceCommands::List => Ok(Output::Namespaces {
+            active: active.to_string(),
+            namespaces: manager.list_namespaces()?,
+        }),
+        NamespaceCommands::Drop { name, force } => {
+            if !manager.drop_namespace(&name, force)? {
+                return Err(CliError::not_found(format!("Namespace not found: {name}")).into());
+            }
+            info!("Dropped namespace {} (force: {})", name, force);
+            Ok(Output::NamespaceDropped { namespace: name })
         }
     }
+}
 
-    info!("Listed {} documents in collection {}", count, collection);
-    Ok(())
+fn handle_collection(manager: &CollectionManager, command: CollectionCommands) -> anyhow::Result<Output> {
+    match command {
+        CollectionCommands::SetCompression { collection, codec, rewrite } => {
+            let codec: CompressionCodec = codec.parse()?;
+            manager.set_compression(&collection, codec)?;
+            info!("Set compression of collection {} to {}", collection, codec);
+            let rewritten = if rewrite { Some(manager.rewrite_compression(&collection)?) } else { None };
+            Ok(Output::CompressionSet {
+                collection,
+                codec: codec.to_string(),
+                rewritten,
+            })
+        }
+        CollectionCommands::Rename { from, to } => {
+            manager.rename_collection(&from, &to)?;
+            info!("Renamed collection {} to {}", from, to);
+            Ok(Output::CollectionRenamed { from, to })
+        }
+        CollectionCommands::Copy { from, to, filter, indexes } => {
+            let filter = match filter.as_deref() {
+                Some([field, value]) => Some((field.clone(), serde_json::from_str(value)?)),
+                _ => None,
+            };
+            let options = CopyOptions { filter, indexes: indexes.into() };
+            let report = manager.copy_collection_with_progress(&from, &to, &options, |progress| {
+                info!(
+                    "Copying {} to {}: read {} of {} documents, copied {}",
+                    from,
+                    to,
+                    progress.documents_copied + progress.documents_skipped,
+                    progress.documents_total,
+                    progress.documents_copied
+                );
+            })?;
+            Ok(Output::CollectionCopied { from, to, report })
+        }
+    }
 }
 
-fn handle_list_collections(manager: &dotdb_core::document::CollectionManager) -> anyhow::Result<()> {
-    let collections = manager.list_collections()?;
-    let count = collections.len();
+fn handle_schema(manager: &CollectionManager, command: SchemaCommands) -> anyhow::Result<Output> {
+    let no_schema = |collection: &str| CliError::not_found(format!("Collection '{collection}' has no schema"));
+    match command {
+        SchemaCommands::Set {
+            collection,
+            schema,
+            validate_existing,
+        } => {
+            let report = manager.set_schema(&collection, &schema, validate_existing)?;
+            info!("Set schema for collection {}", collection);
+            Ok(Output::SchemaSet { collection, report })
+        }
+        SchemaCommands::Get { collection } => match manager.get_schema(&collection)? {
+            Some(schema) => Ok(Output::Schema { collection, schema }),
+            None => Err(no_schema(&collection).into()),
+        },
+        SchemaCommands::Check { collection, .. } => match manager.check_schema(&collection)? {
+            Some(report) => Ok(Output::SchemaChecked { collection, report }),
+            None => Err(no_schema(&collection).into()),
+        },
+        SchemaCommands::Remove { collection } => {
+            if !manager.remove_schema(&collection)? {
+                return Err(no_schema(&collection).into());
+            }
+            info!("Removed schema from collection {}", collection);
+            Ok(Output::SchemaRemoved { collection })
+        }
+    }
+}
 
-    if collections.is_empty() {
-        println!("No collections found");
-    } else {
-        println!("Collections:");
-        for collection in collections {
-            println!("  {collection}");
+fn handle_export(manager: &CollectionManager, file: Option<PathBuf>) -> anyhow::Result<Output> {
+    let summary = match &file {
+        Some(path) => {
+            let mut file = std::io::BufWriter::new(std::fs::File::create(path).with_context(|| format!("Failed to create {}", path.display()))?);
+            export_snapshot(manager.storage().as_ref(), 0, &mut file)?
         }
+        None => export_snapshot(manager.storage().as_ref(), 0, &mut std::io::stdout().lock())?,
+    };
+    Ok(Output::Exported { file, summary })
+}
+
+fn handle_import(manager: &CollectionManager, file: PathBuf) -> anyhow::Result<Output> {
+    let reader = std::io::BufReader::new(std::fs::File::open(&file).with_context(|| format!("Failed to open {}", file.display()))?);
+    let summary = import_snapshot(manager.storage().as_ref(), reader)?;
+    Ok(Output::Imported { file, summary })
+}
+
+fn handle_advisor(manager: &CollectionManager) -> Output {
+    let recommendations = manager.index_recommendations();
+    info!("Listed {} index recommendations", recommendations.len());
+    Output::Recommendations(recommendations)
+}
+
+fn handle_compaction_status(status_path: &std::path::Path) -> anyhow::Result<Output> {
+    if !status_path.exists() {
+        return Ok(Output::Unavailable {
+            path: status_path.to_path_buf(),
+            message: format!("No compaction status found at {}", status_path.display()),
+            detail: "Background compaction has not run against this data directory",
+        });
     }
 
-    info!("Listed {} collections", count);
-    Ok(())
+    let stats = CompactionSchedulerStats::read_from(status_path)?;
+    info!("Read compaction status from {}", status_path.display());
+    Ok(Output::Compaction(stats))
 }
 
-fn handle_create_collection(manager: &dotdb_core::document::CollectionManager, collection: &str) -> anyhow::Result<()> {
-    manager.create_collection(collection)?;
-    println!("Collection created: {collection}");
-    info!("Created collection {}", collection);
-    Ok(())
+fn handle_compaction_run(manager: &CollectionManager, dead_ratio: f64) -> anyhow::Result<Output> {
+    if !(0.0..=1.0).contains(&dead_ratio) {
+        return Err(CliError::validation(format!("--dead-ratio must be between 0 and 1, got {dead_ratio}")).into());
+    }
+    let strategy = DeadSpaceStrategy {
+        dead_ratio_threshold: dead_ratio,
+        ..DeadSpaceStrategy::default()
+    };
+    let report = manager.compact(&strategy)?;
+    info!("Compacted {} segments", report.segments.segments_compacted);
+    Ok(Output::Compacted(report))
 }
 
-fn handle_delete_collection(manager: &dotdb_core::document::CollectionManager, collection: &str) -> anyhow::Result<()> {
-    let deleted = manager.delete_collection(collection)?;
-    if deleted {
-        println!("Collection deleted: {collection}");
-        info!("Deleted collection {}", collection);
-    } else {
-        println!("Collection not found: {collection}");
-        info!("Collection {} not found", collection);
+fn handle_vacuum_status(status_path: &std::path::Path) -> anyhow::Result<Output> {
+    if !status_path.exists() {
+        return Ok(Output::Unavailable {
+            path: status_path.to_path_buf(),
+            message: format!("No vacuum status found at {}", status_path.display()),
+            detail: "The background vacuum has not run against this data directory",
+        });
     }
-    Ok(())
+
+    let status = VacuumStatus::read_from(status_path)?;
+    info!("Read vacuum status from {}", status_path.display());
+    Ok(Output::Vacuum(status))
 }
 
-fn handle_count(manager: &dotdb_core::document::CollectionManager, collection: &str) -> anyhow::Result<()> {
-    let count = manager.count(collection)?;
-    println!("Documents in collection '{collection}': {count}");
-    info!("Counted {} documents in collection {}", count, collection);
-    Ok(())
+fn handle_vacuum_run(data_dir: &std::path::Path, collection: &str) -> anyhow::Result<Output> {
+    std::fs::create_dir_all(data_dir)?;
+    request_collection_vacuum(&data_dir.join(VACUUM_REQUESTS_FILE), collection)?;
+
+    let running = VacuumStatus::read_from(&data_dir.join(VACUUM_STATUS_FILE)).is_ok_and(|status| status.running);
+    Ok(Output::VacuumQueued {
+        collection: collection.to_string(),
+        running,
+    })
 }
 
-fn handle_find(manager: &dotdb_core::document::CollectionManager, collection: &str, field: &str, value_str: &str) -> anyhow::Result<()> {
-    let value: Value = serde_json::from_str(value_str)?;
+fn handle_locks(status_path: &std::path::Path) -> anyhow::Result<Output> {
+    if !status_path.exists() {
+        return Ok(Output::Unavailable {
+            path: status_path.to_path_buf(),
+            message: format!("No lock status found at {}", status_path.display()),
+            detail: "Deadlock detection has not run against this data directory",
+        });
+    }
 
-    let matching_docs = manager.find_by_field(collection, field, &value)?;
-    let count = matching_docs.len();
+    let graph = WaitForGraphSnapshot::read_from(status_path)?;
+    info!("Read lock status from {}", status_path.display());
+    Ok(Output::Locks(graph))
+}
 
-    if matching_docs.is_empty() {
-        println!("No documents found matching {field}={value}");
-    } else {
-        println!("Found {count} documents matching {field}={value}:");
-        for (id, doc) in matching_docs {
-            println!("  {}: {}", id, serde_json::to_string(&doc)?);
+fn handle_stats_show(statistics_path: &std::path::Path, table: &str) -> anyhow::Result<Output> {
+    let Some(freshness) = TableFreshness::read_from(statistics_path, table)? else {
+        return Ok(Output::Unavailable {
+            path: statistics_path.to_path_buf(),
+            message: format!("No statistics found for '{table}' in {}", statistics_path.display()),
+            detail: "The collection has not been analyzed against this data directory",
+        });
+    };
+    info!("Read statistics from {}", statistics_path.display());
+    Ok(Output::Statistics(freshness))
+}
+
+fn handle_stats_space(manager: &CollectionManager, collection: &str) -> anyhow::Result<Output> {
+    let usage = manager.space_usage(collection)?;
+    info!("Measured space usage of collection {collection}");
+    Ok(Output::Space {
+        space_amplification: usage.space_amplification(),
+        compression_ratio: usage.compression_ratio(),
+        usage,
+    })
+}
+
+fn handle_calibrate(data_dir: &std::path::Path) -> anyhow::Result<Output> {
+    std::fs::create_dir_all(data_dir)?;
+    let previous = load_cost_profile(data_dir)?;
+
+    info!("Running calibration benchmarks in {}", data_dir.display());
+    let measurements = calibrate(data_dir, &CalibrationConfig::default())?;
+    let profile = save_cost_profile(data_dir, CostProfile::from_measurements(measurements))?;
+    info!("Saved cost profile to {}", data_dir.join(COST_PROFILE_FILE).display());
+    Ok(Output::Calibrated { profile, previous })
+}
+
+/// Nanoseconds since the Unix epoch of an RFC 3339 time given to `--as-of`
+fn parse_as_of(value: &str) -> anyhow::Result<u64> {
+    let invalid = |reason: String| CliError::validation(format!("--as-of must be an RFC 3339 time such as 2025-06-01T12:00:00Z, got '{value}': {reason}"));
+    let time = DateTime::parse_from_rfc3339(value).map_err(|e| invalid(e.to_string()))?;
+    let nanos = time.timestamp_nanos_opt().and_then(|nanos| u64::try_from(nanos).ok());
+    Ok(nanos.ok_or_else(|| invalid("out of range".to_string()))?)
+}
+
+fn parse_lsn(value: &str) -> anyhow::Result<LogSequenceNumber> {
+    let invalid = || CliError::validation(format!("LSN must be written as FILE_ID/OFFSET, got '{value}'"));
+    let (file_id, offset) = value.split_once('/').ok_or_else(invalid)?;
+    Ok(LogSequenceNumber {
+        file_id: file_id.parse().map_err(|_| invalid())?,
+        offset: offset.parse().map_err(|_| invalid())?,
+    })
+}
+
+fn archived_wal(wal_dir: PathBuf, archive_dir: PathBuf) -> StorageResult<WriteAheadLog> {
+    WriteAheadLog::new(WalConfig {
+        directory: wal_dir,
+        archive: Some(WalArchiveConfig {
+            directory: archive_dir,
+            compression: ArchiveCompression::None,
+        }),
+        ..WalConfig::default()
+    })
+}
+
+fn handle_checkpoint(wal_dir: PathBuf, archive_dir: PathBuf, data_file: PathBuf) -> anyhow::Result<Output> {
+    if !data_file.exists() {
+        return Err(CliError::not_found(format!("page store {} does not exist", data_file.display())).into());
+    }
+    let wal = archived_wal(wal_dir, archive_dir)?;
+    let version = wal.replay(|_| Ok(()))?;
+    let checkpoint = wal.checkpoint_with_base(version, &data_file)?;
+    let base = wal
+        .archive()
+        .and_then(|archive| archive.base_snapshot(checkpoint))
+        .context("base snapshot is missing from the archive")?;
+
+    info!("Checkpoint at {} with base {}", checkpoint, base.display());
+    Ok(Output::Checkpointed {
+        data_file,
+        checkpoint_lsn: checkpoint.to_string(),
+        base,
+    })
+}
+
+/// Rebuild the page store at the target next to the live one: the base snapshot of the
+/// checkpoint replay starts from (an empty store when replay starts at the beginning of the
+/// log) with the committed pages after it applied. The live page store is only replaced by
+/// the rebuilt one once it is synced, so a failed recovery leaves it untouched.
+fn handle_recover(to_timestamp: Option<u64>, to_lsn: Option<&str>, wal_dir: PathBuf, archive_dir: PathBuf, data_file: PathBuf) -> anyhow::Result<Output> {
+    let target = match (to_timestamp, to_lsn) {
+        (Some(seconds), _) => RecoveryTarget::Timestamp(seconds.saturating_mul(1_000_000_000)),
+        (None, Some(lsn)) => RecoveryTarget::Lsn(parse_lsn(lsn)?),
+        (None, None) => return Err(CliError::validation("either --to-timestamp or --to-lsn is required").into()),
+    };
+    let wal = archived_wal(wal_dir, archive_dir)?;
+    let archive = wal.archive().expect("the WAL is opened with an archive");
+    let key = MasterKey::from_env()?.map(Arc::new);
+
+    let rebuilt = data_file.with_extension("recovering");
+    for leftover in [rebuilt.clone(), FileFormat::quarantine_path(&rebuilt)] {
+        if leftover.exists() {
+            std::fs::remove_file(&leftover).with_context(|| format!("Failed to remove {} left by an earlier recovery", leftover.display()))?;
+        }
+    }
+
+    let storage = RefCell::new(None);
+    let mut pages_written = 0usize;
+    let recovered = wal.restore_to(
+        target,
+        |checkpoint| {
+            if let Some(checkpoint) = checkpoint {
+                let base = archive.base_snapshot(checkpoint).ok_or_else(|| {
+                    StorageError::NotFound(format!(
+                        "no base snapshot was stored for the checkpoint at {checkpoint}; only checkpoints written by `dotdb checkpoint` can be recovered from"
+                    ))
+                })?;
+                std::fs::copy(base, &rebuilt)?;
+            }
+            let mut restored = FileFormat::new(StorageConfig {
+                path: rebuilt.clone(),
+                ..StorageConfig::default()
+            });
+            if let Some(key) = &key {
+                restored.set_key_provider(key.clone());
+            }
+            restored.init()?;
+            *storage.borrow_mut() = Some(restored);
+            Ok(())
+        },
+        |entry: &LogEntry| -> StorageResult<()> {
+            if entry.record_type() == RecordType::Write
+                && let Some(mut page) = entry.page()
+            {
+                storage.borrow_mut().as_mut().expect("the base is restored before records are applied").write_page(&mut page)?;
+                pages_written += 1;
+            }
+            Ok(())
+        },
+    );
+    let synced = recovered.and_then(|report| {
+        if let Some(storage) = storage.borrow_mut().as_mut() {
+            storage.sync()?;
         }
+        Ok(report)
+    });
+    drop(storage);
+    let report: RecoveryReport = match synced {
+        Ok(report) => report,
+        Err(e) => {
+            let _ = std::fs::remove_file(&rebuilt);
+            let _ = std::fs::remove_file(FileFormat::quarantine_path(&rebuilt));
+            return Err(e.into());
+        }
+    };
+    std::fs::rename(&rebuilt, &data_file).with_context(|| format!("Failed to replace {} with the recovered page store", data_file.display()))?;
+
+    // The quarantine of the replaced store described pages that no longer exist
+    let (rebuilt_quarantine, quarantine) = (FileFormat::quarantine_path(&rebuilt), FileFormat::quarantine_path(&data_file));
+    if rebuilt_quarantine.exists() {
+        std::fs::rename(&rebuilt_quarantine, &quarantine).with_context(|| format!("Failed to replace {}", quarantine.display()))?;
+    } else if quarantine.exists() {
+        std::fs::remove_file(&quarantine).with_context(|| format!("Failed to remove {}", quarantine.display()))?;
+    }
+
+    info!("Recovered {} transactions into {}", report.transactions_applied, data_file.display());
+    Ok(Output::Recovered {
+        data_file,
+        checkpoint_lsn: report.checkpoint_lsn.map(|lsn| lsn.to_string()),
+        last_applied_lsn: report.last_applied_lsn.map(|lsn| lsn.to_string()),
+        segments_replayed: report.segments_replayed,
+        transactions_applied: report.transactions_applied,
+        transactions_discarded: report.transactions_discarded,
+        pages_written,
+    })
+}
+
+/// Open the page store, with the master key from the environment if one is set
+fn open_page_store(data_file: &std::path::Path) -> anyhow::Result<FileFormat> {
+    let mut storage = FileFormat::new(StorageConfig {
+        path: data_file.to_path_buf(),
+        ..StorageConfig::default()
+    });
+    if let Some(key) = MasterKey::from_env()? {
+        storage.set_key_provider(Arc::new(key));
+    }
+    storage.init()?;
+    Ok(storage)
+}
+
+/// Pages re-encrypted between progress updates by `encryption enable --wait` and `encryption rotate --wait`
+const REENCRYPT_BATCH_PAGES: u64 = 256;
+
+fn handle_encryption(command: EncryptionCommands, data_dir: &std::path::Path) -> anyhow::Result<Output> {
+    let (data_file, wait) = match &command {
+        EncryptionCommands::Status { data_file, .. } => (data_file, false),
+        EncryptionCommands::Enable { data_file, wait } | EncryptionCommands::Rotate { data_file, wait } => (data_file, *wait),
+    };
+    let data_file = data_file.clone().unwrap_or_else(|| data_dir.join("storage.db"));
+    if !data_file.exists() {
+        return Err(CliError::not_found(format!("page store {} does not exist", data_file.display())).into());
+    }
+
+    if let EncryptionCommands::Status { .. } = command {
+        let status = FileFormat::read_encryption_status(&data_file)?;
+        return Ok(Output::Encryption {
+            activated_key: None,
+            report: encryption_report(data_file, &status),
+        });
     }
 
-    info!("Found {} documents in collection {} matching {}={}", count, collection, field, value);
-    Ok(())
+    if std::env::var_os(MASTER_KEY_ENV).is_none() && std::env::var_os(MASTER_KEY_FILE_ENV).is_none() {
+        return Err(CliError::validation(format!("set {MASTER_KEY_ENV} or {MASTER_KEY_FILE_ENV} to the master key")).into());
+    }
+    let mut storage = open_page_store(&data_file)?;
+    let key_id = match command {
+        EncryptionCommands::Enable { .. } => storage.enable_encryption()?,
+        _ => storage.rotate_key()?,
+    };
+
+    if wait {
+        while let Some(batch) = storage.reencrypt_pages(REENCRYPT_BATCH_PAGES)? {
+            info!("Re-encryption reached page {} of {}", batch.progress.next_page, storage.total_pages());
+            if batch.finished {
+                break;
+            }
+        }
+    }
+    storage.sync()?;
+
+    Ok(Output::Encryption {
+        activated_key: Some(key_id),
+        report: encryption_report(data_file, &storage.encryption_status()),
+    })
+}
+
+fn encryption_report(data_file: PathBuf, status: &EncryptionStatus) -> EncryptionReport {
+    let percent_complete = status.rotation_percent().unwrap_or(100.0);
+    let encryption = status.encryption.as_ref();
+    EncryptionReport {
+        data_file,
+        encrypted: status.is_encrypted(),
+        total_pages: status.total_pages,
+        active_key: encryption.map(|encryption| encryption.active_key),
+        keys: encryption
+            .map(|encryption| {
+                encryption
+                    .keys
+                    .iter()
+                    .map(|key| EncryptionKeyReport {
+                        id: key.id,
+                        created_at: DateTime::from_timestamp_nanos(key.created_at as i64).to_rfc3339(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+        reencryption: encryption.and_then(|encryption| encryption.rotation).map(|rotation| ReencryptionReport {
+            next_page: rotation.next_page,
+            pages_rewritten: rotation.pages_rewritten,
+            pages_skipped: rotation.pages_skipped,
+            percent_complete,
+        }),
+    }
+}
+
+/// Scan the page store; corrupt pages are reported with [`output::ExitCode::Corruption`]
+fn handle_verify(data_file: &std::path::Path) -> anyhow::Result<Output> {
+    if !data_file.exists() {
+        return Err(CliError::not_found(format!("page store {} does not exist", data_file.display())).into());
+    }
+
+    let mut storage = open_page_store(data_file)?;
+    let report = storage.verify()?;
+
+    if report.is_clean() {
+        info!("Verified {} pages in {}", report.pages_scanned, data_file.display());
+    } else {
+        error!("Found {} corrupt pages in {}", report.corrupt_pages.len(), data_file.display());
+    }
+    let corrupt_pages = report
+        .corrupt_pages
+        .iter()
+        .map(|page| CorruptPageReport {
+            page_id: page.page_id.0,
+            index: page.page_type.is_index(),
+            stored_checksum: page.stored_checksum,
+            computed_checksum: page.computed_checksum,
+            description: page.describe(),
+        })
+        .collect();
+    Ok(Output::Verified {
+        data_file: data_file.to_path_buf(),
+        pages_scanned: report.pages_scanned,
+        free_pages: report.free_pages,
+        corrupt_pages,
+        corruption_log: storage.corruption_log_path().to_path_buf(),
+    })
 }
@@ -0,0 +1,1073 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Result rendering and exit codes
+//!
+//! Command handlers return an [`Output`] or an error and leave printing to
+//! [`finish`], which renders the result as text or JSON and picks the exit code.
+//! In JSON mode standard output holds exactly one JSON document, including for
+//! errors; logs always go to standard error.
+
+use clap::ValueEnum;
+use dotdb_core::compaction::scheduler::{CompactionSchedulerStats, SchedulerState};
+use dotdb_core::document::{AggregationResult, CopyReport, DocumentCompaction, DocumentError, DocumentId, RecompressionReport, SchemaReport, SnapshotSummary};
+use dotdb_core::statistics::{CollectionSpaceUsage, CostProfile, IndexRecommendation, RecommendedIndexKind, RefreshStatus, StatisticsError, TableFreshness};
+use dotdb_core::storage_engine::{StorageError, VacuumStatus, WaitForGraphSnapshot};
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// Format selected by `--output`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// One JSON document on standard output
+    Json,
+}
+
+/// Process exit codes, stable for scripts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    Success = 0,
+    /// Any failure without a more specific code
+    Failure = 1,
+    /// The document, collection, namespace, schema or file does not exist
+    NotFound = 2,
+    /// Malformed input: invalid JSON, IDs, names, schemas or documents that violate a schema
+    Validation = 3,
+    /// The storage engine or the file system failed
+    Storage = 4,
+    /// A concurrent transaction conflicted; running the command again may succeed
+    Conflict = 5,
+    /// The document being created already exists
+    AlreadyExists = 6,
+    /// `verify` found corrupt pages
+    Corruption = 7,
+}
+
+impl ExitCode {
+    /// Name reported as the `code` of JSON errors
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::Failure => "failure",
+            Self::NotFound => "not_found",
+            Self::Validation => "validation",
+            Self::Storage => "storage",
+            Self::Conflict => "conflict",
+            Self::AlreadyExists => "already_exists",
+            Self::Corruption => "corruption",
+        }
+    }
+}
+
+/// Error raised by the CLI itself, carrying the exit code to report
+#[derive(Debug, thiserror::Error)]
+#[error("{message}")]
+pub struct CliError {
+    pub code: ExitCode,
+    pub message: String,
+}
+
+impl CliError {
+    pub fn new(code: ExitCode, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ExitCode::NotFound, message)
+    }
+
+    pub fn validation(message: impl Into<String>) -> Self {
+        Self::new(ExitCode::Validation, message)
+    }
+}
+
+/// Exit code for an error, from the first error in its chain with a known cause
+pub fn exit_code(error: &anyhow::Error) -> ExitCode {
+    error.chain().find_map(classify).unwrap_or(ExitCode::Failure)
+}
+
+fn classify(error: &(dyn std::error::Error + 'static)) -> Option<ExitCode> {
+    if let Some(error) = error.downcast_ref::<CliError>() {
+        return Some(error.code);
+    }
+    if let Some(error) = error.downcast_ref::<DocumentError>() {
+        return Some(document_exit_code(error));
+    }
+    if let Some(error) = error.downcast_ref::<StorageError>() {
+        return Some(storage_exit_code(error));
+    }
+    if let Some(error) = error.downcast_ref::<StatisticsError>() {
+        return Some(match error {
+            StatisticsError::TableNotFound(_) | StatisticsError::ColumnNotFound(_) => ExitCode::NotFound,
+            StatisticsError::InvalidConfiguration(_) => ExitCode::Validation,
+            StatisticsError::CollectionFailed(_) | StatisticsError::StorageError(_) => ExitCode::Storage,
+        });
+    }
+    if error.is::<serde_json::Error>() || error.is::<uuid::Error>() || error.is::<std::num::ParseIntError>() {
+        return Some(ExitCode::Validation);
+    }
+    if let Some(error) = error.downcast_ref::<io::Error>() {
+        return Some(io_exit_code(error));
+    }
+    None
+}
+
+fn document_exit_code(error: &DocumentError) -> ExitCode {
+    match error {
+        DocumentError::DocumentNotFound(_) | DocumentError::CollectionNotFound(_) | DocumentError::HistoryUnavailable { .. } | DocumentError::SnapshotReleased { .. } => ExitCode::NotFound,
+        DocumentError::JsonSerialization(_)
+        | DocumentError::InvalidDocumentId(_)
+        | DocumentError::InvalidCollectionName(_)
+        | DocumentError::InvalidNamespace(_)
+        | DocumentError::NamespaceNotEmpty(_)
+        | DocumentError::InvalidSchema(_)
+        | DocumentError::InvalidProjection(_)
+        | DocumentError::InvalidAggregation(_)
+        | DocumentError::InvalidCodec(_)
+        | DocumentError::SchemaViolation { .. }
+        | DocumentError::InvalidSnapshot(_) => ExitCode::Validation,
+        DocumentError::DocumentAlreadyExists(_) | DocumentError::CollectionAlreadyExists(_) => ExitCode::AlreadyExists,
+        DocumentError::TransactionAborted { .. } | DocumentError::VersionConflict { .. } => ExitCode::Conflict,
+        DocumentError::Storage(error) => storage_exit_code(error),
+        DocumentError::SnapshotIo(error) => io_exit_code(error),
+        DocumentError::Compression(_) => ExitCode::Corruption,
+        DocumentError::Database(_) | DocumentError::Index(_) | DocumentError::Statistics(_) => ExitCode::Storage,
+        DocumentError::ReadOnlyReplica { .. } | DocumentError::ReplicaBehind { .. } | DocumentError::ChangeFeedTruncated { .. } | DocumentError::ReplicationGap { .. } => ExitCode::Failure,
+    }
+}
+
+fn storage_exit_code(error: &StorageError) -> ExitCode {
+    match error {
+        StorageError::Deadlock { .. } => ExitCode::Conflict,
+        StorageError::Corruption(_) => ExitCode::Corruption,
+        StorageError::NotFound(_) | StorageError::PageNotFound(_) => ExitCode::NotFound,
+        StorageError::Io(error) => io_exit_code(error),
+        _ => ExitCode::Storage,
+    }
+}
+
+fn io_exit_code(error: &io::Error) -> ExitCode {
+    match error.kind() {
+        io::ErrorKind::NotFound => ExitCode::NotFound,
+        io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => ExitCode::Validation,
+        _ => ExitCode::Storage,
+    }
+}
+
+/// Print the result of a command and return the code to exit with
+pub fn finish(format: OutputFormat, result: anyhow::Result<Output>) -> ExitCode {
+    let error = match result {
+        Ok(output) => match output.print(format) {
+            Ok(()) => return output.exit_code(),
+            // Nothing more can be reported once standard output is gone
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return ExitCode::Failure,
+            Err(e) => anyhow::Error::from(e),
+        },
+        Err(error) => error,
+    };
+
+    let code = exit_code(&error);
+    match format {
+        OutputFormat::Json => {
+            let report = serde_json::json!({
+                "error": { "code": code.name(), "exit_code": code as i32, "message": format!("{error:#}") }
+            });
+            println!("{report}");
+        }
+        OutputFormat::Text => eprintln!("Error: {error:#}"),
+    }
+    code
+}
+
+/// A document as listed by `list`
+#[derive(Debug, Serialize)]
+pub struct ListedDocument {
+    pub id: DocumentId,
+    /// The value sorted by, as shown in text output
+    #[serde(skip)]
+    pub sort_value: Option<String>,
+    pub document: Value,
+}
+
+/// A document matched by `find`
+#[derive(Debug, Serialize)]
+pub struct FoundDocument {
+    pub id: DocumentId,
+    pub document: Value,
+    /// Projected paths the document does not have
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<String>,
+}
+
+/// Outcome of one operation of a `tx` script
+#[derive(Debug, Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum TxStep {
+    Put { collection: String, id: DocumentId },
+    Get { collection: String, id: DocumentId, document: Option<Value> },
+    Update { collection: String, id: DocumentId },
+    Delete { collection: String, id: DocumentId, deleted: bool },
+}
+
+/// A page that failed checksum verification
+#[derive(Debug, Serialize)]
+pub struct CorruptPageReport {
+    pub page_id: u64,
+    /// Whether the page only holds index data, which a rebuild restores
+    pub index: bool,
+    pub stored_checksum: u32,
+    pub computed_checksum: u32,
+    pub description: String,
+}
+
+/// Encryption state of the page store
+#[derive(Debug, Serialize)]
+pub struct EncryptionReport {
+    pub data_file: PathBuf,
+    pub encrypted: bool,
+    /// Pages in the store, excluding the file header
+    pub total_pages: u64,
+    pub active_key: Option<u8>,
+    pub keys: Vec<EncryptionKeyReport>,
+    /// Progress of re-encrypting pages with the active key, while any remain
+    pub reencryption: Option<ReencryptionReport>,
+}
+
+/// A data encryption key in the keyring
+#[derive(Debug, Serialize)]
+pub struct EncryptionKeyReport {
+    pub id: u8,
+    /// RFC 3339 time the key was activated
+    pub created_at: String,
+}
+
+/// Progress of re-encryption after encryption was enabled or the key rotated
+#[derive(Debug, Serialize)]
+pub struct ReencryptionReport {
+    pub next_page: u64,
+    pub pages_rewritten: u64,
+    pub pages_skipped: u64,
+    pub percent_complete: f64,
+}
+
+/// Result of a command
+///
+/// Serialized untagged, so each command's JSON output is the object for its own
+/// variant. Status commands serialize the status exactly as the engine publishes it.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum Output {
+    Inserted {
+        collection: String,
+        id: DocumentId,
+    },
+    Document {
+        collection: String,
+        id: DocumentId,
+        document: Value,
+        /// Version to pass to `--if-version`; not reported for reads as of a past time
+        #[serde(skip_serializing_if = "Option::is_none")]
+        version: Option<u64>,
+        /// The time the document was read as of, as given
+        #[serde(skip_serializing_if = "Option::is_none")]
+        as_of: Option<String>,
+    },
+    Updated {
+        collection: String,
+        id: DocumentId,
+        /// The document's new version, when the update was conditional on the old one
+        #[serde(skip_serializing_if = "Option::is_none")]
+        version: Option<u64>,
+    },
+    Deleted {
+        collection: String,
+        id: DocumentId,
+    },
+    Documents {
+        collection: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        sort: Option<String>,
+        documents: Vec<ListedDocument>,
+    },
+    Collections {
+        namespace: String,
+        collections: Vec<String>,
+    },
+    CollectionCreated {
+        collection: String,
+    },
+    CollectionDeleted {
+        collection: String,
+    },
+    CollectionRenamed {
+        from: String,
+        to: String,
+    },
+    CollectionCopied {
+        from: String,
+        to: String,
+        #[serde(flatten)]
+        report: CopyReport,
+    },
+    Count {
+        collection: String,
+        count: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        as_of: Option<String>,
+    },
+    Found {
+        collection: String,
+        field: String,
+        value: Value,
+        documents: Vec<FoundDocument>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        as_of: Option<String>,
+    },
+    Aggregated {
+        collection: String,
+        #[serde(flatten)]
+        result: AggregationResult,
+    },
+    Transaction {
+        transaction_id: u64,
+        operations: Vec<TxStep>,
+    },
+    NamespaceCreated {
+        namespace: String,
+        created: bool,
+    },
+    Namespaces {
+        active: String,
+        namespaces: Vec<String>,
+    },
+    NamespaceDropped {
+        namespace: String,
+    },
+    CompressionSet {
+        collection: String,
+        codec: String,
+        /// What storing the existing documents again did, when asked to
+        #[serde(skip_serializing_if = "Option::is_none")]
+        rewritten: Option<RecompressionReport>,
+    },
+    SchemaSet {
+        collection: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        report: Option<SchemaReport>,
+    },
+    Schema {
+        collection: String,
+        schema: Value,
+    },
+    SchemaChecked {
+        collection: String,
+        #[serde(flatten)]
+        report: SchemaReport,
+    },
+    SchemaRemoved {
+        collection: String,
+    },
+    Recommendations(Vec<IndexRecommendation>),
+    Exported {
+        /// `None` when the snapshot itself went to standard output
+        #[serde(skip_serializing_if = "Option::is_none")]
+        file: Option<PathBuf>,
+        #[serde(flatten)]
+        summary: SnapshotSummary,
+    },
+    Imported {
+        file: PathBuf,
+        #[serde(flatten)]
+        summary: SnapshotSummary,
+    },
+    Compaction(CompactionSchedulerStats),
+    Compacted(DocumentCompaction),
+    Vacuum(VacuumStatus),
+    VacuumQueued {
+        collection: String,
+        /// Whether a running vacuum will pick the request up
+        running: bool,
+    },
+    Locks(WaitForGraphSnapshot),
+    Statistics(TableFreshness),
+    Space {
+        #[serde(flatten)]
+        usage: CollectionSpaceUsage,
+        /// On-disk size over logical size
+        space_amplification: f64,
+        /// Decompressed document size over stored document size
+        compression_ratio: f64,
+    },
+    /// A status file the running engine publishes has not been written yet
+    Unavailable {
+        path: PathBuf,
+        message: String,
+        detail: &'static str,
+    },
+    Calibrated {
+        #[serde(flatten)]
+        profile: CostProfile,
+        #[serde(skip)]
+        previous: Option<CostProfile>,
+    },
+    Checkpointed {
+        data_file: PathBuf,
+        checkpoint_lsn: String,
+        base: PathBuf,
+    },
+    Recovered {
+        data_file: PathBuf,
+        checkpoint_lsn: Option<String>,
+        last_applied_lsn: Option<String>,
+        segments_replayed: usize,
+        transactions_applied: usize,
+        transactions_discarded: usize,
+        pages_written: usize,
+    },
+    Verified {
+        data_file: PathBuf,
+        pages_scanned: u64,
+        free_pages: u64,
+        corrupt_pages: Vec<CorruptPageReport>,
+        corruption_log: PathBuf,
+    },
+    Encryption {
+        /// Key activated by `encryption enable` or `encryption rotate`
+        #[serde(skip_serializing_if = "Option::is_none")]
+        activated_key: Option<u8>,
+        #[serde(flatten)]
+        report: EncryptionReport,
+    },
+    /// Interactive commands print as they go
+    None,
+}
+
+impl Output {
+    /// Exit code for a command that ran to completion
+    pub fn exit_code(&self) -> ExitCode {
+        match self {
+            Self::SchemaChecked { report, .. } if !report.is_valid() => ExitCode::Validation,
+            Self::Verified { corrupt_pages, .. } if !corrupt_pages.is_empty() => ExitCode::Corruption,
+            _ => ExitCode::Success,
+        }
+    }
+
+    fn print(&self, format: OutputFormat) -> io::Result<()> {
+        if matches!(self, Self::None) {
+            return Ok(());
+        }
+        // A snapshot exported to standard output keeps it to itself
+        let mut out: Box<dyn Write> = if matches!(self, Self::Exported { file: None, .. }) {
+            Box::new(io::stderr().lock())
+        } else {
+            Box::new(io::stdout().lock())
+        };
+        match format {
+            OutputFormat::Json => {
+                serde_json::to_writer_pretty(&mut out, self)?;
+                writeln!(out)?;
+            }
+            OutputFormat::Text => self.write_text(&mut out)?,
+        }
+        out.flush()
+    }
+
+    fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        match self {
+            Self::Inserted { id, .. } => writeln!(out, "Document inserted with ID: {id}"),
+            Self::Document { document, .. } => writeln!(out, "{document}"),
+            Self::Updated { id, version: Some(version), .. } => writeln!(out, "Document updated: {id} (now version {version})"),
+            Self::Updated { id, .. } => writeln!(out, "Document updated: {id}"),
+            Self::Deleted { id, .. } => writeln!(out, "Document deleted: {id}"),
+            Self::Documents { collection, sort, documents } => {
+                if documents.is_empty() {
+                    return writeln!(out, "No documents found in collection '{collection}'");
+                }
+                writeln!(out, "Documents in collection '{collection}':")?;
+                for document in documents {
+                    match (sort, &document.sort_value) {
+                        (Some(path), Some(value)) => writeln!(out, "  {}  {path}={value}", document.id)?,
+                        _ => writeln!(out, "  {}", document.id)?,
+                    }
+                }
+                Ok(())
+            }
+            Self::Collections { namespace, collections } => {
+                if collections.is_empty() {
+                    return writeln!(out, "No collections found in namespace {namespace}");
+                }
+                writeln!(out, "Collections in namespace {namespace}:")?;
+                for collection in collections {
+                    writeln!(out, "  {collection}")?;
+                }
+                Ok(())
+            }
+            Self::CollectionCreated { collection } => writeln!(out, "Collection created: {collection}"),
+            Self::CollectionDeleted { collection } => writeln!(out, "Collection deleted: {collection}"),
+            Self::CollectionRenamed { from, to } => writeln!(out, "Collection renamed: {from} -> {to}"),
+            Self::CollectionCopied { from, to, report } => {
+                writeln!(
+                    out,
+                    "Copied {} of {} documents from '{from}' to '{to}' ({} filtered out)",
+                    report.documents_copied, report.documents_total, report.documents_skipped
+                )?;
+                for paths in &report.indexes {
+                    writeln!(out, "Covering index on {}", paths.join(","))?;
+                }
+                writeln!(out, "Source read as of snapshot {}; later writes to '{from}' are not copied", report.snapshot)
+            }
+            Self::Count { collection, count, as_of } => match as_of {
+                Some(as_of) => writeln!(out, "Documents in collection '{collection}' as of {as_of}: {count}"),
+                None => writeln!(out, "Documents in collection '{collection}': {count}"),
+            },
+            Self::Found { field, value, documents, as_of, .. } => {
+                let as_of = as_of.as_ref().map(|as_of| format!(" as of {as_of}")).unwrap_or_default();
+                if documents.is_empty() {
+                    return writeln!(out, "No documents found matching {field}={value}{as_of}");
+                }
+                writeln!(out, "Found {} documents matching {field}={value}{as_of}:", documents.len())?;
+                for document in documents {
+                    if document.missing.is_empty() {
+                        writeln!(out, "  {}: {}", document.id, document.document)?;
+                    } else {
+                        writeln!(out, "  {}: {} (missing: {})", document.id, document.document, document.missing.join(", "))?;
+                    }
+                }
+                Ok(())
+            }
+            Self::Aggregated { collection, result } => write_aggregation(out, collection, result),
+            Self::Transaction { operations, .. } => {
+                for step in operations {
+                    match step {
+                        TxStep::Put { id, .. } => writeln!(out, "Document inserted with ID: {id}")?,
+                        TxStep::Get { document: Some(document), .. } => writeln!(out, "{document}")?,
+                        TxStep::Get { document: None, .. } => writeln!(out, "Document not found")?,
+                        TxStep::Update { id, .. } => writeln!(out, "Document updated: {id}")?,
+                        TxStep::Delete { id, deleted: true, .. } => writeln!(out, "Document deleted: {id}")?,
+                        TxStep::Delete { id, deleted: false, .. } => writeln!(out, "Document not found: {id}")?,
+                    }
+                }
+                writeln!(out, "Transaction committed")
+            }
+            Self::NamespaceCreated { namespace, created: true } => writeln!(out, "Namespace created: {namespace}"),
+            Self::NamespaceCreated { namespace, created: false } => writeln!(out, "Namespace already exists: {namespace}"),
+            Self::Namespaces { active, namespaces } => {
+                writeln!(out, "Namespaces:")?;
+                for namespace in namespaces {
+                    let marker = if namespace == active { " (active)" } else { "" };
+                    writeln!(out, "  {namespace}{marker}")?;
+                }
+                Ok(())
+            }
+            Self::NamespaceDropped { namespace } => writeln!(out, "Namespace dropped: {namespace}"),
+            Self::CompressionSet { collection, codec, rewritten } => {
+                writeln!(out, "Compression of collection '{collection}' set to {codec}")?;
+                match rewritten {
+                    Some(report) => writeln!(
+                        out,
+                        "Rewrote {} documents: {} before, {} after",
+                        report.documents,
+                        format_bytes(report.bytes_before),
+                        format_bytes(report.bytes_after)
+                    ),
+                    None => Ok(()),
+                }
+            }
+            Self::SchemaSet { collection, report } => {
+                writeln!(out, "Schema set for collection '{collection}'")?;
+                match report {
+                    Some(report) => write_schema_report(out, collection, report),
+                    None => Ok(()),
+                }
+            }
+            Self::Schema { schema, .. } => writeln!(out, "{}", serde_json::to_string_pretty(schema).map_err(io::Error::from)?),
+            Self::SchemaChecked { collection, report } => write_schema_report(out, collection, report),
+            Self::SchemaRemoved { collection } => writeln!(out, "Schema removed from collection '{collection}'"),
+            Self::Recommendations(recommendations) => write_recommendations(out, recommendations),
+            Self::Exported { summary, .. } => writeln!(out, "Exported {}", SnapshotDescription(summary)),
+            Self::Imported { summary, .. } => writeln!(out, "Imported {}", SnapshotDescription(summary)),
+            Self::Compaction(stats) => write_compaction(out, stats),
+            Self::Compacted(report) => {
+                writeln!(out, "Compaction finished")?;
+                writeln!(out, "  Tombstones dropped: {}", report.tombstones_dropped)?;
+                writeln!(out, "  Versions dropped:   {}", report.versions_dropped)?;
+                writeln!(
+                    out,
+                    "  Segments:           {} rewritten, {} dead entries dropped",
+                    report.segments.segments_compacted, report.segments.dead_entries_dropped
+                )?;
+                writeln!(
+                    out,
+                    "  Reclaimed:          {} ({} of live data copied)",
+                    format_bytes(report.segments.bytes_reclaimed),
+                    format_bytes(report.segments.bytes_rewritten)
+                )
+            }
+            Self::Vacuum(status) => write_vacuum(out, status),
+            Self::VacuumQueued { collection, running } => {
+                writeln!(out, "Queued vacuum of collection {collection}")?;
+                if *running {
+                    writeln!(out, "Run `dotdb vacuum status` to see the result")
+                } else {
+                    writeln!(out, "No running vacuum found; the request runs when the storage engine next starts")
+                }
+            }
+            Self::Locks(graph) => write_locks(out, graph),
+            Self::Statistics(freshness) => write_statistics(out, freshness),
+            Self::Space {
+                usage,
+                space_amplification,
+                compression_ratio,
+            } => {
+                writeln!(out, "Space usage of {}", usage.collection)?;
+                writeln!(out, "  Documents:          {}", usage.documents)?;
+                writeln!(out, "  Document bodies:    {}", format_bytes(usage.document_bytes))?;
+                writeln!(out, "  Stored bodies:      {} ({compression_ratio:.2}x compression)", format_bytes(usage.stored_document_bytes))?;
+                writeln!(out, "  Logical size:       {}", format_bytes(usage.logical_bytes))?;
+                writeln!(out, "  On disk:            {}", format_bytes(usage.on_disk_bytes))?;
+                writeln!(out, "  Dead entries:       {} ({})", usage.dead_entries, format_bytes(usage.dead_bytes()))?;
+                writeln!(out, "  History tombstones: {}", usage.history_tombstones)?;
+                writeln!(out, "  Amplification:      {space_amplification:.2}x")
+            }
+            Self::Unavailable { message, detail, .. } => {
+                writeln!(out, "{message}")?;
+                writeln!(out, "{detail}")
+            }
+            Self::Calibrated { profile, previous } => write_calibration(out, profile, previous.as_ref()),
+            Self::Checkpointed { data_file, checkpoint_lsn, base } => {
+                writeln!(out, "Checkpoint written at LSN {checkpoint_lsn}")?;
+                writeln!(out, "Base snapshot of {}: {}", data_file.display(), base.display())
+            }
+            Self::Recovered {
+                data_file,
+                checkpoint_lsn,
+                last_applied_lsn,
+                segments_replayed,
+                transactions_applied,
+                transactions_discarded,
+                pages_written,
+            } => {
+                match checkpoint_lsn {
+                    Some(lsn) => writeln!(out, "Replayed from checkpoint at LSN {lsn}")?,
+                    None => writeln!(out, "Replayed from the beginning of the log")?,
+                }
+                writeln!(out, "Segments read: {segments_replayed}")?;
+                writeln!(out, "Transactions applied: {transactions_applied}")?;
+                writeln!(out, "Transactions discarded: {transactions_discarded}")?;
+                writeln!(out, "Pages written: {pages_written}")?;
+                writeln!(out, "Recovered page store: {}", data_file.display())?;
+                if let Some(lsn) = last_applied_lsn {
+                    writeln!(out, "Last applied LSN: {lsn}")?;
+                }
+                Ok(())
+            }
+            Self::Verified {
+                data_file,
+                pages_scanned,
+                free_pages,
+                corrupt_pages,
+                corruption_log,
+            } => {
+                writeln!(out, "Verified {}", data_file.display())?;
+                writeln!(out, "  Pages scanned: {pages_scanned}")?;
+                writeln!(out, "  Free pages:    {free_pages}")?;
+                writeln!(out, "  Corrupt pages: {}", corrupt_pages.len())?;
+                if corrupt_pages.is_empty() {
+                    return writeln!(out, "All page checksums are valid");
+                }
+
+                writeln!(out, "Corrupt pages:")?;
+                for page in corrupt_pages {
+                    writeln!(out, "  {}", page.description)?;
+                }
+                let index_pages = corrupt_pages.iter().filter(|page| page.index).count();
+                if index_pages > 0 {
+                    writeln!(out, "{index_pages} of the corrupt pages hold index data and can be recovered with rebuild")?;
+                }
+                if index_pages < corrupt_pages.len() {
+                    writeln!(out, "Primary data is damaged; restore it with `dotdb recover` from the WAL archive")?;
+                }
+                writeln!(out, "Corrupt pages were quarantined and recorded in {}", corruption_log.display())
+            }
+            Self::Encryption { activated_key, report } => write_encryption(out, *activated_key, report),
+            Self::None => Ok(()),
+        }
+    }
+}
+
+/// Snapshot contents in words
+struct SnapshotDescription<'a>(&'a SnapshotSummary);
+
+impl fmt::Display for SnapshotDescription<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} documents in {} collections across {} namespaces", self.0.documents, self.0.collections, self.0.namespaces)
+    }
+}
+
+fn write_schema_report(out: &mut dyn Write, collection: &str, report: &SchemaReport) -> io::Result<()> {
+    if report.is_valid() {
+        return writeln!(out, "All {} documents in '{collection}' match the schema", report.documents_checked);
+    }
+
+    writeln!(out, "{} of {} documents in '{collection}' do not match the schema:", report.invalid.len(), report.documents_checked)?;
+    for document in &report.invalid {
+        writeln!(out, "  {}", document.id)?;
+        for violation in &document.violations {
+            writeln!(out, "    {violation}")?;
+        }
+    }
+    Ok(())
+}
+
+fn write_recommendations(out: &mut dyn Write, recommendations: &[IndexRecommendation]) -> io::Result<()> {
+    if recommendations.is_empty() {
+        writeln!(out, "No index recommendations")?;
+        return writeln!(out, "Run some queries first; fields need enough lookups in the window to be considered");
+    }
+
+    writeln!(out, "Recommended indexes (best first):")?;
+    for (rank, rec) in recommendations.iter().enumerate() {
+        let kind = match rec.index_kind {
+            RecommendedIndexKind::Hash => "hash",
+            RecommendedIndexKind::BTree => "btree",
+        };
+        writeln!(out, "  {}. {}.{} ({kind})", rank + 1, rec.collection, rec.field)?;
+        writeln!(out, "     Accesses:     {} equality, {} range, {} sort", rec.equality_lookups, rec.range_scans, rec.sorts)?;
+        writeln!(out, "     Scans saved:  {} ({} documents filtered)", rec.scans_avoided, rec.rows_filtered)?;
+        writeln!(out, "     Benefit:      {:.0} (maintenance {:.0})", rec.estimated_benefit, rec.estimated_maintenance_cost)?;
+    }
+    Ok(())
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 { format!("{bytes} B") } else { format!("{value:.1} {}", UNITS[unit]) }
+}
+
+fn unix_now() -> std::time::Duration {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default()
+}
+
+fn write_compaction(out: &mut dyn Write, stats: &CompactionSchedulerStats) -> io::Result<()> {
+    let state = match stats.state {
+        SchedulerState::Running => "running",
+        SchedulerState::Paused => "paused (foreground write latency too high)",
+        SchedulerState::Stopped => "stopped",
+    };
+    let age = unix_now().as_secs().saturating_sub(stats.updated_at);
+
+    writeln!(out, "Compaction scheduler: {state}")?;
+    writeln!(out, "  Last update:  {age}s ago")?;
+    writeln!(out, "  Strategy:     {}", stats.strategy)?;
+    writeln!(out, "  Threads:      {}", stats.threads)?;
+    let budget = if stats.io_budget_bytes_per_sec == 0 {
+        "unlimited".to_string()
+    } else {
+        format!("{}/s", format_bytes(stats.io_budget_bytes_per_sec))
+    };
+    writeln!(out, "  Throughput:   {}/s (budget {budget})", format_bytes(stats.throughput_bytes_per_sec))?;
+    if let Some(p99) = stats.write_latency_p99_us {
+        writeln!(out, "  Write p99:    {:.2} ms", p99 as f64 / 1000.0)?;
+    }
+    writeln!(out, "  Queued:       {}", stats.queued_tasks)?;
+    writeln!(
+        out,
+        "  Completed:    {} ({} files, {} read, {} written)",
+        stats.completed_tasks,
+        stats.files_compacted,
+        format_bytes(stats.bytes_read),
+        format_bytes(stats.bytes_written)
+    )?;
+    writeln!(out, "  Failed:       {}", stats.failed_tasks)?;
+    writeln!(out, "  Paused:       {} times, {:.1}s total", stats.pause_count, stats.total_paused_ms as f64 / 1000.0)?;
+
+    if stats.active_tasks.is_empty() {
+        return writeln!(out, "No compactions in progress");
+    }
+    writeln!(out, "Active compactions:")?;
+    for task in &stats.active_tasks {
+        writeln!(
+            out,
+            "  #{}: {} files, {:.1}% of {} ({}s)",
+            task.task_id,
+            task.input_files,
+            task.fraction_complete() * 100.0,
+            format_bytes(task.input_bytes),
+            task.elapsed_ms / 1000
+        )?;
+    }
+    Ok(())
+}
+
+fn write_vacuum(out: &mut dyn Write, status: &VacuumStatus) -> io::Result<()> {
+    let age = unix_now().as_secs().saturating_sub(status.updated_at);
+
+    writeln!(out, "Vacuum: {}", if status.running { "running" } else { "stopped" })?;
+    writeln!(out, "  Last update:  {age}s ago")?;
+    writeln!(out, "  Watermark:    {}", status.watermark)?;
+    let window = if status.retain_for_secs == 0 {
+        "none".to_string()
+    } else {
+        format!("{}s", status.retain_for_secs)
+    };
+    writeln!(out, "  Retention:    {} historical versions, window {window}", status.keep_history)?;
+    writeln!(out, "  Versions:     {} across {} pages", status.total_versions, status.total_pages)?;
+    writeln!(
+        out,
+        "  Reclaimed:    {} versions, {} over {} runs",
+        status.versions_reclaimed,
+        format_bytes(status.bytes_reclaimed),
+        status.runs
+    )?;
+    if !status.named_snapshots.is_empty() {
+        writeln!(out, "  Pinned by:    {}", status.named_snapshots.join(", "))?;
+    }
+    if !status.pending_collections.is_empty() {
+        writeln!(out, "  Queued:       {}", status.pending_collections.join(", "))?;
+    }
+
+    if let Some(run) = &status.last_run {
+        let scope = run.collection.as_deref().map_or("all pages".to_string(), |collection| format!("collection {collection}"));
+        let progress = if run.completed { "complete" } else { "partial, resumes next cycle" };
+        writeln!(
+            out,
+            "Last run ({scope}, {progress}): {} versions, {} from {} pages in {} ms",
+            run.versions_reclaimed,
+            format_bytes(run.bytes_reclaimed),
+            run.pages_scanned,
+            run.duration_ms
+        )?;
+    }
+    Ok(())
+}
+
+fn write_aggregation(out: &mut dyn Write, collection: &str, result: &AggregationResult) -> io::Result<()> {
+    fn render(map: &serde_json::Map<String, Value>) -> String {
+        map.iter().map(|(name, value)| format!("{name}={value}")).collect::<Vec<_>>().join(" ")
+    }
+
+    if result.groups.is_empty() {
+        writeln!(out, "No groups in collection '{collection}' ({} documents scanned)", result.documents_scanned)?;
+    } else {
+        writeln!(
+            out,
+            "{} groups from {} of {} documents in collection '{collection}':",
+            result.groups.len(),
+            result.documents_aggregated,
+            result.documents_scanned
+        )?;
+    }
+    for group in &result.groups {
+        let mut key = String::new();
+        if let (Some(start), Some(end)) = (group.window_start, group.window_end) {
+            key.push_str(&format!("[{start}, {end}) "));
+        }
+        if !group.keys.is_empty() {
+            key.push_str(&render(&group.keys));
+            key.push(' ');
+        }
+        writeln!(out, "  {key}{}", render(&group.values))?;
+    }
+
+    let warnings = &result.warnings;
+    if warnings.total() > 0 {
+        writeln!(
+            out,
+            "Skipped {} documents without a timestamp, {} non-numeric values and {} values that would overflow",
+            warnings.missing_timestamp, warnings.non_numeric, warnings.overflow
+        )?;
+    }
+    Ok(())
+}
+
+fn write_encryption(out: &mut dyn Write, activated_key: Option<u8>, report: &EncryptionReport) -> io::Result<()> {
+    if let Some(key_id) = activated_key {
+        writeln!(out, "Activated data encryption key {key_id}")?;
+    }
+    if !report.encrypted {
+        return writeln!(out, "{} is not encrypted", report.data_file.display());
+    }
+
+    writeln!(out, "Encryption of {}: AES-256-GCM", report.data_file.display())?;
+    if let Some(active_key) = report.active_key {
+        writeln!(out, "  Active key:    {active_key}")?;
+    }
+    let keys: Vec<String> = report.keys.iter().map(|key| format!("{} (activated {})", key.id, key.created_at)).collect();
+    writeln!(out, "  Keys:          {}", keys.join(", "))?;
+    writeln!(out, "  Pages:         {}", report.total_pages)?;
+    match &report.reencryption {
+        Some(progress) => writeln!(
+            out,
+            "  Re-encryption: {:.1}% (next page {}), {} pages rewritten, {} skipped",
+            progress.percent_complete, progress.next_page, progress.pages_rewritten, progress.pages_skipped
+        ),
+        None if report.keys.len() > 1 => writeln!(out, "Older keys are kept because some pages could not be re-encrypted; run `dotdb verify` to find corrupt pages"),
+        None => writeln!(out, "All pages are encrypted with the active key"),
+    }
+}
+
+fn write_locks(out: &mut dyn Write, graph: &WaitForGraphSnapshot) -> io::Result<()> {
+    writeln!(out, "Wait-for graph ({}s ago, victim policy: {})", unix_now().as_secs().saturating_sub(graph.updated_at), graph.policy)?;
+
+    if graph.transactions.is_empty() {
+        return writeln!(out, "No transactions are holding or waiting on locks");
+    }
+
+    writeln!(out, "{:<12} {:>6} {:>12}  Victim", "Transaction", "Locks", "Written")?;
+    for txn in &graph.transactions {
+        writeln!(
+            out,
+            "{:<12} {:>6} {:>12}  {}",
+            txn.transaction_id,
+            txn.locks_held,
+            format_bytes(txn.bytes_written),
+            if txn.deadlock_victim { "yes" } else { "" }
+        )?;
+    }
+
+    if graph.edges.is_empty() {
+        writeln!(out, "No transactions are waiting")?;
+    } else {
+        writeln!(out, "Waiting:")?;
+        for edge in &graph.edges {
+            writeln!(out, "  {} -> {}  page {} ({:?}, {} ms)", edge.waiter, edge.holder, edge.resource, edge.lock_mode, edge.waited_ms)?;
+        }
+    }
+
+    for cycle in &graph.cycles {
+        let path: Vec<String> = cycle.iter().chain(cycle.first()).map(|txn| txn.to_string()).collect();
+        writeln!(out, "Deadlock: {}", path.join(" -> "))?;
+    }
+    Ok(())
+}
+
+fn write_statistics(out: &mut dyn Write, freshness: &TableFreshness) -> io::Result<()> {
+    let now = unix_now().as_nanos() as u64;
+    let ago = |timestamp: u64| format!("{}s ago", now.saturating_sub(timestamp) / 1_000_000_000);
+
+    writeln!(out, "Statistics for {}", freshness.table)?;
+    writeln!(out, "  Rows:          {}", freshness.row_count)?;
+    writeln!(out, "  Last analyzed: {}", freshness.last_analyzed.map_or("never".to_string(), ago))?;
+    writeln!(
+        out,
+        "  Changed since: {} inserts, {} updates, {} deletes ({:.1}% of rows)",
+        freshness.mutations.inserts,
+        freshness.mutations.updates,
+        freshness.mutations.deletes,
+        freshness.mutation_ratio() * 100.0
+    )?;
+
+    let Some(refresh) = &freshness.last_refresh else {
+        return writeln!(out, "No refresh has run");
+    };
+    let status = match &refresh.status {
+        RefreshStatus::Completed => "completed".to_string(),
+        RefreshStatus::Cancelled => "cancelled".to_string(),
+        RefreshStatus::Failed(reason) => format!("failed: {reason}"),
+    };
+    writeln!(out, "Last refresh ({:?}, {}): {status}", refresh.trigger, ago(refresh.started_at))?;
+    writeln!(out, "  Sampled:       {} of {} rows in {} ms", refresh.sampled_rows, refresh.row_count, refresh.duration_ms)?;
+    for (column, boundaries) in &refresh.bucket_boundaries {
+        let shown: Vec<String> = boundaries.iter().take(8).map(|bound| format!("{bound:.2}")).collect();
+        let more = if boundaries.len() > shown.len() {
+            format!(", ... ({} buckets)", boundaries.len())
+        } else {
+            String::new()
+        };
+        writeln!(out, "  {column}: [{}{more}]", shown.join(", "))?;
+    }
+    Ok(())
+}
+
+fn write_calibration(out: &mut dyn Write, profile: &CostProfile, previous: Option<&CostProfile>) -> io::Result<()> {
+    let measurements = &profile.measurements;
+    writeln!(out, "Measured ({} byte pages)", measurements.page_size)?;
+    writeln!(out, "  Sequential page read: {:>10.0} ns", measurements.sequential_page_read_ns)?;
+    writeln!(out, "  Random page read:     {:>10.0} ns", measurements.random_page_read_ns)?;
+    writeln!(out, "  Comparison:           {:>10.1} ns", measurements.comparison_ns)?;
+    writeln!(out, "  Hash probe:           {:>10.1} ns", measurements.hash_probe_ns)?;
+
+    writeln!(out, "Cost profile v{} (relative to one sequential page read)", profile.version)?;
+    let mut row = |name: &str, value: f64, previous: Option<f64>| match previous {
+        Some(previous) => writeln!(out, "  {name:<21} {value:>10.4}  (was {previous:.4})"),
+        None => writeln!(out, "  {name:<21} {value:>10.4}"),
+    };
+    row("Random page cost:", profile.random_page_cost, previous.map(|p| p.random_page_cost))?;
+    row("CPU cost per row:", profile.cpu_cost_per_row, previous.map(|p| p.cpu_cost_per_row))?;
+    row("Hash probe cost:", profile.hash_probe_cost, previous.map(|p| p.hash_probe_cost))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes_follow_the_error_chain() {
+        let missing: anyhow::Error = DocumentError::DocumentNotFound(DocumentId::new()).into();
+        assert_eq!(exit_code(&missing), ExitCode::NotFound);
+        assert_eq!(exit_code(&missing.context("while updating")), ExitCode::NotFound);
+
+        let invalid_json = serde_json::from_str::<Value>("{").unwrap_err();
+        assert_eq!(exit_code(&invalid_json.into()), ExitCode::Validation);
+        assert_eq!(exit_code(&DocumentId::from_string("not-a-uuid").unwrap_err().into()), ExitCode::Validation);
+        assert_eq!(exit_code(&DocumentError::InvalidNamespace("a b".into()).into()), ExitCode::Validation);
+        assert_eq!(exit_code(&DocumentError::DocumentAlreadyExists(DocumentId::new()).into()), ExitCode::AlreadyExists);
+        let conflict = DocumentError::VersionConflict {
+            id: DocumentId::new(),
+            expected: 2,
+            current: 3,
+        };
+        assert_eq!(exit_code(&conflict.into()), ExitCode::Conflict);
+        assert_eq!(exit_code(&DocumentError::Storage(StorageError::Corruption("bad page".into())).into()), ExitCode::Corruption);
+        assert_eq!(exit_code(&io::Error::other("disk full").into()), ExitCode::Storage);
+        assert_eq!(exit_code(&CliError::new(ExitCode::Conflict, "retry").into()), ExitCode::Conflict);
+        assert_eq!(exit_code(&anyhow::anyhow!("something else")), ExitCode::Failure);
+    }
+
+    #[test]
+    fn test_json_output_is_one_object_per_command() {
+        let id = DocumentId::new();
+        let output = Output::Document {
+            collection: "users".into(),
+            id: id.clone(),
+            document: serde_json::json!({"name": "Alice"}),
+            version: Some(3),
+            as_of: None,
+        };
+        let json = serde_json::to_value(&output).unwrap();
+        assert_eq!(json, serde_json::json!({"collection": "users", "id": id, "document": {"name": "Alice"}, "version": 3}));
+
+        let report = SchemaReport {
+            documents_checked: 2,
+            invalid: Vec::new(),
+        };
+        let checked = Output::SchemaChecked { collection: "users".into(), report };
+        assert_eq!(checked.exit_code(), ExitCode::Success);
+        assert_eq!(serde_json::to_value(&checked).unwrap()["documents_checked"], 2);
+    }
+}
@@ -0,0 +1,435 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Interactive shell
+//!
+//! Keeps a single collection manager open for the whole session and runs the
+//! document commands against it. Input spanning several lines is collected until
+//! every brace and bracket outside a JSON string is closed.
+//!
+//! `.snapshot begin` pins a read snapshot: until `.snapshot end`, `get`, `list`,
+//! `find` and `count` see every collection as it was when the snapshot began,
+//! while writes go on against the live database.
+
+use chrono::DateTime;
+use dotdb_core::document::{CollectionManager, DocumentId, ReadSnapshot};
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Context, Editor, Helper};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+/// Commands that take a collection name as their first argument
+const COLLECTION_COMMANDS: &[&str] = &["put", "get", "update", "delete", "list", "find", "count"];
+
+/// Every command the shell understands, for completion
+const COMMANDS: &[&str] = &[
+    "put",
+    "get",
+    "update",
+    "delete",
+    "list",
+    "find",
+    "count",
+    "collections",
+    "help",
+    ".output",
+    ".snapshot",
+    ".help",
+    ".exit",
+];
+
+const HELP: &str = "\
+Commands:
+  put <collection> <json>             Insert a document
+  get <collection> <id>               Get a document by ID
+  update <collection> <id> <json>     Replace a document
+  delete <collection> <id>            Delete a document
+  list <collection>                   List document IDs
+  find <collection> <field> <json>    Find documents by field value
+  count <collection>                  Count documents
+  collections                         List collections
+  .output json|table                  Set the result format
+  .snapshot begin|end                 Read every collection as of now, until ended
+  .exit                               Close the database and leave the shell
+
+JSON arguments may span several lines. Press Ctrl+C to discard the current input.";
+
+/// How query results are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Json,
+    Table,
+}
+
+/// Line editor helper providing completion and multi-line input
+struct ShellHelper {
+    /// Collection names offered for completion, refreshed after every command
+    collections: Vec<String>,
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        let start = before.rfind(char::is_whitespace).map(|i| i + 1).unwrap_or(0);
+        let word = &before[start..];
+        let previous: Vec<&str> = before[..start].split_whitespace().collect();
+
+        let candidates: Vec<&str> = match previous.as_slice() {
+            [] => COMMANDS.to_vec(),
+            [command] if COLLECTION_COMMANDS.contains(command) => self.collections.iter().map(String::as_str).collect(),
+            [".output"] => vec!["json", "table"],
+            [".snapshot"] => vec!["begin", "end"],
+            _ => Vec::new(),
+        };
+
+        let pairs = candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(word))
+            .map(|candidate| Pair {
+                display: candidate.to_string(),
+                replacement: candidate.to_string(),
+            })
+            .collect();
+        Ok((start, pairs))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {
+    fn validate(&self, ctx: &mut ValidationContext<'_>) -> rustyline::Result<ValidationResult> {
+        if open_delimiters(ctx.input()) > 0 {
+            Ok(ValidationResult::Incomplete)
+        } else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
+
+impl Helper for ShellHelper {}
+
+/// Number of `{` and `[` left unclosed, ignoring those inside JSON strings
+pub(crate) fn open_delimiters(input: &str) -> i64 {
+    let mut depth = 0i64;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in input.chars() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => depth += 1,
+            '}' | ']' => depth -= 1,
+            _ => {}
+        }
+    }
+    depth
+}
+
+/// Split off `count` whitespace-separated words, returning them and the trimmed remainder
+pub(crate) fn split_words(input: &str, count: usize) -> (Vec<&str>, &str) {
+    let mut words = Vec::with_capacity(count);
+    let mut rest = input.trim_start();
+    while words.len() < count && !rest.is_empty() {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        words.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    (words, rest.trim_end())
+}
+
+/// Interactive session over an open collection manager
+struct Shell<'a> {
+    manager: &'a CollectionManager,
+    format: OutputFormat,
+    /// Read snapshot the read commands go through, from `.snapshot begin`
+    snapshot: Option<ReadSnapshot>,
+}
+
+/// What the shell should do after a line has been handled
+enum Flow {
+    Continue,
+    Exit,
+}
+
+impl<'a> Shell<'a> {
+    fn new(manager: &'a CollectionManager, json: bool) -> Self {
+        Self {
+            manager,
+            format: if json { OutputFormat::Json } else { OutputFormat::Table },
+            snapshot: None,
+        }
+    }
+
+    fn prompt(&self) -> &'static str {
+        if self.snapshot.is_some() { "dotdb (snapshot)> " } else { "dotdb> " }
+    }
+
+    /// Execute one complete input
+    fn execute(&mut self, input: &str) -> anyhow::Result<Flow> {
+        let (words, rest) = split_words(input, 1);
+        let Some(&command) = words.first() else {
+            return Ok(Flow::Continue);
+        };
+
+        match command {
+            ".exit" | ".quit" => return Ok(Flow::Exit),
+            ".help" | "help" => println!("{HELP}"),
+            ".output" => match rest {
+                "json" => self.format = OutputFormat::Json,
+                "table" => self.format = OutputFormat::Table,
+                "" => println!("Output format: {}", if self.format == OutputFormat::Json { "json" } else { "table" }),
+                other => anyhow::bail!("Unknown output format '{other}', expected json or table"),
+            },
+            ".snapshot" => match rest {
+                "begin" => {
+                    anyhow::ensure!(self.snapshot.is_none(), "A snapshot is already open; end it with .snapshot end first");
+                    let snapshot = self.manager.begin_read_snapshot()?;
+                    println!("Reading as of {} until .snapshot end", format_timestamp(snapshot.timestamp()));
+                    self.snapshot = Some(snapshot);
+                }
+                "end" => {
+                    let snapshot = self.snapshot.take().ok_or_else(|| anyhow::anyhow!("No snapshot is open"))?;
+                    if snapshot.release() {
+                        println!("Snapshot ended");
+                    } else {
+                        println!("Snapshot ended; it had already been released for being held too long");
+                    }
+                }
+                "" => match &self.snapshot {
+                    Some(snapshot) => println!("Reading as of {}", format_timestamp(snapshot.timestamp())),
+                    None => println!("No snapshot is open"),
+                },
+                other => anyhow::bail!("Unknown snapshot command '{other}', expected begin or end"),
+            },
+            "put" => {
+                let (args, json) = split_words(rest, 1);
+                let [collection] = args[..] else { anyhow::bail!("Usage: put <collection> <json>") };
+                let value: Value = serde_json::from_str(json)?;
+                let id = self.manager.insert_value(collection, value)?;
+                println!("Document inserted with ID: {id}");
+            }
+            "get" => {
+                let (args, _) = split_words(rest, 2);
+                let [collection, id] = args[..] else { anyhow::bail!("Usage: get <collection> <id>") };
+                let id_value = DocumentId::from_string(id)?;
+                let document = match &self.snapshot {
+                    Some(snapshot) => snapshot.get_value(collection, &id_value)?,
+                    None => self.manager.get_value(collection, &id_value)?,
+                };
+                match document {
+                    Some(document) => self.print_documents(&[(id.to_string(), document)])?,
+                    None => println!("Document not found"),
+                }
+            }
+            "update" => {
+                let (args, json) = split_words(rest, 2);
+                let [collection, id] = args[..] else { anyhow::bail!("Usage: update <collection> <id> <json>") };
+                let value: Value = serde_json::from_str(json)?;
+                self.manager.update_value(collection, &DocumentId::from_string(id)?, value)?;
+                println!("Document updated: {id}");
+            }
+            "delete" => {
+                let (args, _) = split_words(rest, 2);
+                let [collection, id] = args[..] else { anyhow::bail!("Usage: delete <collection> <id>") };
+                if self.manager.delete(collection, &DocumentId::from_string(id)?)? {
+                    println!("Document deleted: {id}");
+                } else {
+                    println!("Document not found: {id}");
+                }
+            }
+            "list" => {
+                let (args, _) = split_words(rest, 1);
+                let [collection] = args[..] else { anyhow::bail!("Usage: list <collection>") };
+                let ids = match &self.snapshot {
+                    Some(snapshot) => snapshot.list_document_ids(collection)?,
+                    None => self.manager.list_document_ids(collection)?,
+                };
+                let ids: Vec<String> = ids.iter().map(ToString::to_string).collect();
+                self.print_column("id", &ids)?;
+            }
+            "find" => {
+                let (args, value) = split_words(rest, 2);
+                let [collection, field] = args[..] else { anyhow::bail!("Usage: find <collection> <field> <json>") };
+                let value: Value = serde_json::from_str(value)?;
+                let found = match &self.snapshot {
+                    Some(snapshot) => snapshot.find_by_field(collection, field, &value)?,
+                    None => self.manager.find_by_field(collection, field, &value)?,
+                };
+                let documents: Vec<(String, Value)> = found.into_iter().map(|(id, doc)| (id.to_string(), doc)).collect();
+                self.print_documents(&documents)?;
+            }
+            "count" => {
+                let (args, _) = split_words(rest, 1);
+                let [collection] = args[..] else { anyhow::bail!("Usage: count <collection>") };
+                let count = match &self.snapshot {
+                    Some(snapshot) => snapshot.count(collection)?,
+                    None => self.manager.count(collection)?,
+                };
+                match self.format {
+                    OutputFormat::Json => println!("{}", serde_json::json!({ "collection": collection, "count": count })),
+                    OutputFormat::Table => println!("{count}"),
+                }
+            }
+            "collections" => {
+                let collections = self.manager.list_collections()?;
+                self.print_column("collection", &collections)?;
+            }
+            other => anyhow::bail!("Unknown command '{other}'. Type .help for a list of commands"),
+        }
+
+        Ok(Flow::Continue)
+    }
+
+    /// Print a single-column result
+    fn print_column(&self, header: &str, values: &[String]) -> anyhow::Result<()> {
+        match self.format {
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(values)?),
+            OutputFormat::Table => {
+                let rows: Vec<Vec<String>> = values.iter().map(|v| vec![v.clone()]).collect();
+                print_table(&[header.to_string()], &rows);
+            }
+        }
+        Ok(())
+    }
+
+    /// Print documents, one row per document with a column per top-level field
+    fn print_documents(&self, documents: &[(String, Value)]) -> anyhow::Result<()> {
+        match self.format {
+            OutputFormat::Json => {
+                let map: serde_json::Map<String, Value> = documents.iter().cloned().collect();
+                let output = match documents {
+                    [(_, document)] => document.clone(),
+                    _ => Value::Object(map),
+                };
+                println!("{}", serde_json::to_string_pretty(&output)?);
+            }
+            OutputFormat::Table => {
+                let fields: BTreeSet<&str> = documents.iter().filter_map(|(_, doc)| doc.as_object()).flat_map(|obj| obj.keys().map(String::as_str)).collect();
+
+                let mut headers = vec!["id".to_string()];
+                headers.extend(fields.iter().map(|f| f.to_string()));
+                let rows: Vec<Vec<String>> = documents
+                    .iter()
+                    .map(|(id, doc)| {
+                        let mut row = vec![id.clone()];
+                        row.extend(fields.iter().map(|field| match doc.get(*field) {
+                            Some(Value::String(s)) => s.clone(),
+                            Some(value) => value.to_string(),
+                            None => String::new(),
+                        }));
+                        row
+                    })
+                    .collect();
+                print_table(&headers, &rows);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// RFC 3339 form of a snapshot timestamp in nanoseconds since the Unix epoch
+fn format_timestamp(timestamp: u64) -> String {
+    DateTime::from_timestamp_nanos(timestamp as i64).to_rfc3339()
+}
+
+/// Print rows as an aligned text table
+fn print_table(headers: &[String], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let format_row = |cells: &[String]| cells.iter().zip(&widths).map(|(cell, width)| format!("{cell:<width$}")).collect::<Vec<_>>().join(" | ");
+
+    println!("{}", format_row(headers));
+    println!("{}", widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>().join("-+-"));
+    for row in rows {
+        println!("{}", format_row(row));
+    }
+    println!("({} row{})", rows.len(), if rows.len() == 1 { "" } else { "s" });
+}
+
+/// Run the interactive shell until `.exit` or end of input, then flush the database
+///
+/// With `json` the shell starts with `.output json` selected.
+pub fn run(manager: &CollectionManager, history_path: PathBuf, json: bool) -> anyhow::Result<()> {
+    let mut editor: Editor<ShellHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(ShellHelper {
+        collections: manager.list_collections()?,
+    }));
+    if editor.load_history(&history_path).is_err() {
+        info!("No shell history at {}", history_path.display());
+    }
+
+    println!("DotDB shell. Type .help for commands, .exit to quit.");
+    let mut shell = Shell::new(manager, json);
+
+    loop {
+        match editor.readline(shell.prompt()) {
+            Ok(line) => {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                editor.add_history_entry(line.as_str())?;
+
+                match shell.execute(&line) {
+                    Ok(Flow::Continue) => {}
+                    Ok(Flow::Exit) => break,
+                    Err(e) => eprintln!("Error: {e}"),
+                }
+
+                if let (Some(helper), Ok(collections)) = (editor.helper_mut(), manager.list_collections()) {
+                    helper.collections = collections;
+                }
+            }
+            // Ctrl+C discards the line being edited but keeps the session
+            Err(ReadlineError::Interrupted) => println!("^C"),
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    if let Err(e) = editor.save_history(&history_path) {
+        warn!("Failed to save shell history to {}: {}", history_path.display(), e);
+    }
+    manager.flush()?;
+    info!("Shell closed, database flushed");
+    Ok(())
+}
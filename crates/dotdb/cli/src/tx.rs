@@ -0,0 +1,183 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Transaction command
+//!
+//! Runs a script of document operations inside a single transaction. Operations are
+//! read one per line, with JSON arguments allowed to span lines as in the shell, and
+//! the transaction commits only if every operation succeeds.
+
+use crate::output::{CliError, ExitCode, Output, TxStep};
+use crate::shell::{open_delimiters, split_words};
+use clap::ValueEnum;
+use dotdb_core::document::{CollectionManager, DocumentId, DocumentTransaction};
+use dotdb_core::storage_engine::IsolationLevel;
+use serde_json::Value;
+use std::io::Read;
+use std::path::Path;
+use tracing::info;
+
+/// Isolation level accepted by `--isolation`
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum IsolationArg {
+    ReadUncommitted,
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+
+impl From<IsolationArg> for IsolationLevel {
+    fn from(arg: IsolationArg) -> Self {
+        match arg {
+            IsolationArg::ReadUncommitted => IsolationLevel::ReadUncommitted,
+            IsolationArg::ReadCommitted => IsolationLevel::ReadCommitted,
+            IsolationArg::RepeatableRead => IsolationLevel::RepeatableRead,
+            IsolationArg::Serializable => IsolationLevel::Serializable,
+        }
+    }
+}
+
+/// Split a script into operations, joining lines until every brace and bracket is closed
+fn operations(script: &str) -> Vec<String> {
+    let mut operations = Vec::new();
+    let mut current = String::new();
+
+    for line in script.lines() {
+        if current.is_empty() && (line.trim().is_empty() || line.trim_start().starts_with('#')) {
+            continue;
+        }
+        current.push_str(line);
+        current.push('\n');
+        if open_delimiters(&current) <= 0 {
+            operations.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.trim().is_empty() {
+        operations.push(current);
+    }
+    operations
+}
+
+/// Error for an operation with the wrong arguments
+fn usage(syntax: &str) -> anyhow::Error {
+    CliError::validation(format!("Usage: {syntax}")).into()
+}
+
+/// Run one operation inside the transaction
+fn execute(txn: &mut DocumentTransaction, input: &str) -> anyhow::Result<Option<TxStep>> {
+    let (words, rest) = split_words(input, 1);
+    let Some(&command) = words.first() else {
+        return Ok(None);
+    };
+
+    let step = match command {
+        "put" => {
+            let (args, json) = split_words(rest, 1);
+            let [collection] = args[..] else { return Err(usage("put <collection> <json>")) };
+            let value: Value = serde_json::from_str(json)?;
+            let id = txn.insert_value(collection, value)?;
+            TxStep::Put {
+                collection: collection.to_string(),
+                id,
+            }
+        }
+        "get" => {
+            let (args, _) = split_words(rest, 2);
+            let [collection, id] = args[..] else { return Err(usage("get <collection> <id>")) };
+            let id = DocumentId::from_string(id)?;
+            let document = txn.get_value(collection, &id)?;
+            TxStep::Get {
+                collection: collection.to_string(),
+                id,
+                document,
+            }
+        }
+        "update" => {
+            let (args, json) = split_words(rest, 2);
+            let [collection, id] = args[..] else { return Err(usage("update <collection> <id> <json>")) };
+            let value: Value = serde_json::from_str(json)?;
+            let id = DocumentId::from_string(id)?;
+            txn.update_value(collection, &id, value)?;
+            TxStep::Update {
+                collection: collection.to_string(),
+                id,
+            }
+        }
+        "delete" => {
+            let (args, _) = split_words(rest, 2);
+            let [collection, id] = args[..] else { return Err(usage("delete <collection> <id>")) };
+            let id = DocumentId::from_string(id)?;
+            let deleted = txn.delete(collection, &id)?;
+            TxStep::Delete {
+                collection: collection.to_string(),
+                id,
+                deleted,
+            }
+        }
+        other => return Err(CliError::validation(format!("Unknown transaction operation '{other}', expected put, get, update or delete")).into()),
+    };
+    Ok(Some(step))
+}
+
+/// Run the operations in `file` (or standard input) as one transaction
+pub fn run(manager: &CollectionManager, isolation: IsolationArg, file: Option<&Path>) -> anyhow::Result<Output> {
+    let script = match file {
+        Some(path) => std::fs::read_to_string(path)?,
+        None => {
+            let mut script = String::new();
+            std::io::stdin().read_to_string(&mut script)?;
+            script
+        }
+    };
+
+    let mut txn = manager.begin_transaction(isolation.into());
+    let txn_id = txn.id();
+    let mut steps = Vec::new();
+    for (index, operation) in operations(&script).iter().enumerate() {
+        match execute(&mut txn, operation) {
+            Ok(step) => steps.extend(step),
+            Err(e) => {
+                if let Some(document_error) = e.downcast_ref::<dotdb_core::document::DocumentError>()
+                    && document_error.is_retryable()
+                {
+                    let message = format!(
+                        "Operation {} conflicted with a concurrent transaction, nothing was committed; run the transaction again: {e}",
+                        index + 1
+                    );
+                    return Err(CliError::new(ExitCode::Conflict, message).into());
+                }
+                // Keep the cause in the chain so the exit code reflects why the operation failed
+                return Err(e.context(format!("Operation {} failed, transaction rolled back", index + 1)));
+            }
+        }
+    }
+
+    match txn.commit() {
+        Ok(()) => {}
+        Err(e) if e.is_retryable() => {
+            let message = format!("Commit conflicted with a concurrent transaction, nothing was committed; run the transaction again: {e}");
+            return Err(CliError::new(ExitCode::Conflict, message).into());
+        }
+        Err(e) => return Err(e.into()),
+    }
+    manager.flush()?;
+
+    info!("Committed transaction {} at {:?}", txn_id, IsolationLevel::from(isolation));
+    Ok(Output::Transaction {
+        transaction_id: txn_id,
+        operations: steps,
+    })
+}
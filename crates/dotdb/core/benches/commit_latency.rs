@@ -0,0 +1,161 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Commit latency under a write-heavy load
+//!
+//! Every commit dirties a few pages and appends a durable WAL record. Compares
+//! the p99 commit latency of writing dirty pages back on the committing thread
+//! once `max_dirty_pages` is reached, as commits used to, against leaving them to
+//! the background writers with watermark backpressure.
+//!
+//! Criterion reports means, so this bench times each commit itself and prints
+//! percentiles.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use dotdb_core::storage_engine::{BufferManager, BufferPool, FileFormat, GroupCommitConfig, LogEntry, PageId, PageType, StorageConfig, VersionId, WalConfig, WriteAheadLog, WriterThroughput};
+
+const PAGES: usize = 2048;
+const PAGES_PER_COMMIT: usize = 4;
+const COMMITS: usize = 4000;
+const MAX_DIRTY_PAGES: usize = 512;
+
+struct Setup {
+    _dir: tempfile::TempDir,
+    config: StorageConfig,
+    file_format: Arc<Mutex<FileFormat>>,
+    wal: WriteAheadLog,
+}
+
+fn setup() -> Setup {
+    let dir = tempfile::tempdir().unwrap();
+    let config = StorageConfig {
+        path: dir.path().join("commit_latency.db"),
+        buffer_pool_size: PAGES * 2,
+        flush_interval_ms: 0,
+        max_dirty_pages: MAX_DIRTY_PAGES,
+        writer_threads: 2,
+        ..StorageConfig::default()
+    };
+    let mut file_format = FileFormat::new(config.clone());
+    file_format.init().unwrap();
+    let wal = WriteAheadLog::new(WalConfig {
+        directory: dir.path().join("wal"),
+        max_file_size: 64 * 1024 * 1024,
+        direct_io: false,
+        archive: None,
+        group_commit: Some(GroupCommitConfig::default()),
+    })
+    .unwrap();
+    Setup {
+        _dir: dir,
+        config,
+        file_format: Arc::new(Mutex::new(file_format)),
+        wal,
+    }
+}
+
+/// Pages dirtied by commit `commit`, spread over the whole working set
+fn pages_of(commit: usize, page_ids: &[PageId]) -> impl Iterator<Item = PageId> + '_ {
+    (0..PAGES_PER_COMMIT).map(move |j| page_ids[(commit * 7919 + j * 613) % page_ids.len()])
+}
+
+fn log_commit(wal: &WriteAheadLog, txn_id: u64) {
+    let lsn = wal.next_lsn().unwrap();
+    wal.append_durable(&LogEntry::commit_transaction(lsn, txn_id)).unwrap();
+}
+
+/// The committing thread writes every dirty page back once `max_dirty_pages` is reached
+fn synchronous_writeback() -> Vec<Duration> {
+    let setup = setup();
+    let mut pool = BufferPool::new(Arc::clone(&setup.file_format), &setup.config);
+    let page_ids: Vec<PageId> = (0..PAGES).map(|_| pool.allocate_page(PageType::Data, VersionId(1)).unwrap()).collect();
+
+    (0..COMMITS)
+        .map(|commit| {
+            for page_id in pages_of(commit, &page_ids) {
+                pool.get_page_mut(page_id).unwrap().page_mut().data[0] = commit as u8;
+            }
+            let started = Instant::now();
+            if pool.dirty_page_count() >= MAX_DIRTY_PAGES {
+                pool.flush_all().unwrap();
+            }
+            log_commit(&setup.wal, commit as u64);
+            started.elapsed()
+        })
+        .collect()
+}
+
+/// Background writers flush from the start watermark; the commit only waits out backpressure
+fn background_writeback() -> (Vec<Duration>, Vec<WriterThroughput>) {
+    let setup = setup();
+    let mut manager = BufferManager::new(Arc::clone(&setup.file_format), &setup.config);
+    let page_ids: Vec<PageId> = (0..PAGES).map(|_| manager.allocate_page(PageType::Data, VersionId(1)).unwrap()).collect();
+
+    let latencies = (0..COMMITS)
+        .map(|commit| {
+            for page_id in pages_of(commit, &page_ids) {
+                let guard = manager.get_page_for_update(page_id).unwrap();
+                let mut data = guard.page().data.clone();
+                data[0] = commit as u8;
+                guard.update(data).unwrap();
+            }
+            let started = Instant::now();
+            manager.throttle_commit().unwrap();
+            log_commit(&setup.wal, commit as u64);
+            started.elapsed()
+        })
+        .collect();
+
+    let writers = manager.writer_stats();
+    manager.close().unwrap();
+    (latencies, writers)
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    sorted[((sorted.len() - 1) as f64 * p).round() as usize]
+}
+
+fn report(name: &str, mut latencies: Vec<Duration>) -> Duration {
+    latencies.sort();
+    let p99 = percentile(&latencies, 0.99);
+    println!(
+        "{name:<24} p50 {:>10.3?}  p99 {:>10.3?}  p99.9 {:>10.3?}  max {:>10.3?}",
+        percentile(&latencies, 0.50),
+        p99,
+        percentile(&latencies, 0.999),
+        latencies[latencies.len() - 1]
+    );
+    p99
+}
+
+fn main() {
+    println!("commit_latency: {COMMITS} commits of {PAGES_PER_COMMIT} pages over {PAGES} pages, max_dirty_pages = {MAX_DIRTY_PAGES}");
+    let before = report("synchronous_writeback", synchronous_writeback());
+    let (latencies, writers) = background_writeback();
+    let after = report("background_writeback", latencies);
+    for writer in writers {
+        println!(
+            "  writer {}: {} pages, {:.0} pages/s while writing, {:.0}% busy",
+            writer.writer,
+            writer.pages_written,
+            writer.pages_per_sec(),
+            writer.utilization() * 100.0
+        );
+    }
+    println!("p99 commit latency: {:.1}x lower", before.as_secs_f64() / after.as_secs_f64().max(f64::EPSILON));
+}
@@ -0,0 +1,99 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Read and write latency of document compression codecs
+//!
+//! Inserts and reads the same verbose JSON documents in collections compressed
+//! with each codec, and prints the compression ratio each codec reaches on them
+//! so the latency cost can be weighed against the space saved.
+
+use criterion::{BatchSize, Criterion, black_box, criterion_group, criterion_main};
+use dotdb_core::document::{CollectionManager, CompressionCodec, DocumentId, create_in_memory_collection_manager};
+use serde_json::{Value, json};
+
+const DOCUMENTS: u64 = 1000;
+
+const CODECS: [(&str, CompressionCodec); 4] = [
+    ("none", CompressionCodec::None),
+    ("lz4", CompressionCodec::Lz4),
+    ("zstd_3", CompressionCodec::Zstd { level: 3 }),
+    ("zstd_19", CompressionCodec::Zstd { level: 19 }),
+];
+
+/// An event document of the kind that makes up most stored data
+fn document(i: u64) -> Value {
+    json!({
+        "event_type": "http_request",
+        "service": "checkout-api",
+        "timestamp": format!("2025-06-01T12:{:02}:{:02}Z", (i / 60) % 60, i % 60),
+        "request": {"method": "POST", "path": format!("/v1/orders/{i}/items"), "headers": {"content-type": "application/json", "user-agent": "dotlanth-client/1.0"}},
+        "response": {"status": 200, "latency_ms": i % 250, "body_bytes": 512 + i % 1024},
+        "tags": ["production", "eu-west-1", "checkout"],
+        "user": {"id": format!("user_{}", i % 97), "plan": "enterprise", "region": "eu-west-1"}
+    })
+}
+
+fn manager_with(codec: CompressionCodec) -> CollectionManager {
+    let manager = create_in_memory_collection_manager().unwrap();
+    manager.set_compression("events", codec).unwrap();
+    manager
+}
+
+fn bench_document_compression(c: &mut Criterion) {
+    for (name, codec) in CODECS {
+        let manager = manager_with(codec);
+        for i in 0..DOCUMENTS {
+            manager.insert_value("events", document(i)).unwrap();
+        }
+        let usage = manager.space_usage("events").unwrap();
+        println!(
+            "{name}: {} bytes of documents stored in {} ({:.2}x)",
+            usage.document_bytes,
+            usage.stored_document_bytes,
+            usage.compression_ratio()
+        );
+    }
+
+    // Every insert goes to a fresh store so the collection's growing document list does not skew later runs
+    let mut group = c.benchmark_group("compressed_insert");
+    for (name, codec) in CODECS {
+        group.bench_function(name, |b| {
+            b.iter_batched_ref(
+                || manager_with(codec),
+                |manager| black_box(manager.insert_value("events", document(7)).unwrap()),
+                BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("compressed_read");
+    for (name, codec) in CODECS {
+        let manager = manager_with(codec);
+        let ids: Vec<DocumentId> = (0..DOCUMENTS).map(|i| manager.insert_value("events", document(i)).unwrap()).collect();
+        let mut next = 0;
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                next = (next + 1) % ids.len();
+                black_box(manager.get_value("events", &ids[next]).unwrap())
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(compression_benches, bench_document_compression);
+criterion_main!(compression_benches);
@@ -0,0 +1,64 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! B+ tree index construction benchmarks
+//!
+//! Compares building an index over 1M keys with the bulk loader against
+//! inserting the same keys one at a time, as index backfill used to do.
+
+use criterion::{BatchSize, Criterion, Throughput, black_box, criterion_group, criterion_main};
+use dotdb_core::indices::{BPlusTree, Index};
+
+const KEY_COUNT: u64 = 1_000_000;
+const ORDER: usize = 128;
+
+/// Every key exactly once, in a scrambled but reproducible order (7919 is coprime with `KEY_COUNT`)
+fn shuffled_entries() -> Vec<(u64, String)> {
+    (0..KEY_COUNT).map(|i| (i * 7919 % KEY_COUNT, i.to_string())).collect()
+}
+
+fn bench_index_build(c: &mut Criterion) {
+    let entries = shuffled_entries();
+
+    let mut group = c.benchmark_group("bplus_tree_build_1m");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(KEY_COUNT));
+
+    group.bench_function("incremental_insert", |b| {
+        b.iter_batched(
+            || entries.clone(),
+            |entries| {
+                let mut tree = BPlusTree::with_order(ORDER);
+                for (key, value) in entries {
+                    tree.insert(key, value).unwrap();
+                }
+                black_box(tree)
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    for fill_factor in [0.67, 1.0] {
+        group.bench_function(format!("bulk_load_fill_{fill_factor}"), |b| {
+            b.iter_batched(|| entries.clone(), |entries| black_box(BPlusTree::from_entries(ORDER, fill_factor, entries).unwrap()), BatchSize::LargeInput)
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(index_benches, bench_index_build);
+criterion_main!(index_benches);
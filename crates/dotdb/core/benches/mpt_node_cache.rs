@@ -0,0 +1,121 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Random lookups in a trie of 1M keys, with and without the node cache
+//!
+//! The trie is built in memory and its live nodes copied into a database. Both
+//! tries then read the same database-backed nodes, so every node the uncached
+//! trie visits is fetched and decoded again. The cached trie starts cold and
+//! is warmed by one pass over the lookup keys before it is timed.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use dotdb_core::state::mpt::trie::NodeStorage;
+use dotdb_core::state::mpt::{DEFAULT_NODE_CACHE_BYTES, NodeId, NodeType};
+use dotdb_core::state::{Database, MerklePatriciaTrie, MptStorageAdapter};
+
+const KEYS: u64 = 1_000_000;
+const LOOKUPS: usize = 100_000;
+/// Distinct keys looked up, so the hot paths are revisited as in a real workload
+const HOT_KEYS: u64 = 20_000;
+/// Inserts between dropping the nodes earlier inserts replaced, to keep the build in memory
+const COMPACT_EVERY: u64 = 100_000;
+
+fn key(i: u64) -> Vec<u8> {
+    // Spread keys over the whole keyspace rather than sharing long prefixes
+    i.wrapping_mul(0x9E37_79B9_7F4A_7C15).to_be_bytes().to_vec()
+}
+
+fn lookup_keys() -> Vec<Vec<u8>> {
+    let mut state = 0x2545_F491_4F6C_DD1Du64;
+    (0..LOOKUPS)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            key(state % HOT_KEYS * (KEYS / HOT_KEYS))
+        })
+        .collect()
+}
+
+/// Copy the nodes reachable from `id`, leaving behind the ones inserts replaced
+fn copy_live(from: &impl NodeStorage, to: &mut impl NodeStorage, id: NodeId) {
+    if to.contains_node(&id) {
+        return;
+    }
+    let node = from.get_node(&id).unwrap().unwrap();
+    match &node.node_type {
+        NodeType::Extension { child, .. } => copy_live(from, to, *child),
+        NodeType::Branch { children, .. } => children.iter().flatten().for_each(|child| copy_live(from, to, *child)),
+        NodeType::Leaf { .. } | NodeType::Empty => {}
+    }
+    to.put_node(&node).unwrap();
+}
+
+fn run(name: &str, trie: &MerklePatriciaTrie<MptStorageAdapter>, keys: &[Vec<u8>]) -> Duration {
+    let started = Instant::now();
+    for key in keys {
+        assert!(trie.get(key).unwrap().is_some());
+    }
+    let elapsed = started.elapsed();
+    println!("{name:>8}: {:.2}us per lookup", elapsed.as_secs_f64() * 1e6 / keys.len() as f64);
+    elapsed
+}
+
+fn main() {
+    let started = Instant::now();
+    let mut built = MerklePatriciaTrie::new_in_memory();
+    for i in 0..KEYS {
+        built.put(key(i), i.to_le_bytes().to_vec()).unwrap();
+        if (i + 1) % COMPACT_EVERY == 0 {
+            let root = built.root_hash();
+            let mut live = MerklePatriciaTrie::new_in_memory();
+            copy_live(&*built.get_storage_mut().read(), &mut *live.get_storage_mut().write(), root);
+            live.set_root(root);
+            built = live;
+        }
+    }
+
+    let root = built.root_hash();
+    let database = Arc::new(Database::new_in_memory().unwrap());
+    copy_live(&*built.get_storage_mut().read(), &mut MptStorageAdapter::new(database.clone()), root);
+    drop(built);
+    println!("mpt_node_cache: {KEYS} keys stored in {:.1}s, {LOOKUPS} lookups over {HOT_KEYS} keys", started.elapsed().as_secs_f64());
+
+    let mut trie = MerklePatriciaTrie::new(MptStorageAdapter::new(database.clone()));
+    trie.set_root(root);
+    let keys = lookup_keys();
+    let uncached = run("uncached", &trie, &keys);
+
+    let mut cached = MerklePatriciaTrie::new(MptStorageAdapter::new(database)).with_node_cache(DEFAULT_NODE_CACHE_BYTES);
+    cached.set_root(root);
+    for key in &keys {
+        cached.get(key).unwrap();
+    }
+    let after = run("cached", &cached, &keys);
+
+    let stats = cached.stats().cache.unwrap();
+    println!(
+        "  cache: {} nodes, {:.1} MiB of {:.0} MiB, {:.1}% hit rate, {} evictions",
+        stats.entries,
+        stats.bytes as f64 / (1024.0 * 1024.0),
+        stats.capacity_bytes as f64 / (1024.0 * 1024.0),
+        stats.hit_rate() * 100.0,
+        stats.evictions
+    );
+    println!("lookups: {:.1}x faster", uncached.as_secs_f64() / after.as_secs_f64().max(f64::EPSILON));
+}
@@ -0,0 +1,134 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Page IO throughput and IOPS of the storage backends
+//!
+//! Reads and writes batches of pages through buffered pread/pwrite, direct IO with
+//! pread/pwrite and, with the `io-uring` feature on Linux, direct IO through io_uring.
+//! Sequential workloads report bytes per second and random workloads pages per second.
+//! Direct IO modes are skipped where the temporary directory's filesystem refuses
+//! `O_DIRECT`, as tmpfs does; point TMPDIR at a disk-backed directory to include them.
+
+use std::path::Path;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use dotdb_core::io::{AlignedBuffer, PreadPageIO};
+use dotdb_core::storage_engine::AsyncIO;
+
+const PAGE_SIZE: usize = 4096;
+const FILE_PAGES: u64 = 16 * 1024;
+const BATCH: usize = 64;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+const QUEUE_DEPTH: u32 = 64;
+
+fn open_modes(dir: &Path) -> Vec<(&'static str, Box<dyn AsyncIO>)> {
+    let mut modes: Vec<(&'static str, Box<dyn AsyncIO>)> = vec![("buffered", Box::new(PreadPageIO::open(&dir.join("buffered"), PAGE_SIZE, false).unwrap()))];
+
+    let direct = PreadPageIO::open(&dir.join("direct_pwrite"), PAGE_SIZE, true).unwrap();
+    if !direct.is_direct() {
+        println!("{} does not support O_DIRECT, skipping direct IO modes", dir.display());
+        return modes;
+    }
+    modes.push(("direct_pwrite", Box::new(direct)));
+
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    match dotdb_core::io::UringPageIO::open(&dir.join("direct_io_uring"), PAGE_SIZE, true, QUEUE_DEPTH) {
+        Ok(io) => modes.push(("direct_io_uring", Box::new(io))),
+        Err(e) => println!("io_uring unavailable ({e}), skipping direct_io_uring"),
+    }
+
+    modes
+}
+
+/// Page IDs of one batch: consecutive for sequential workloads, scattered over the file otherwise
+fn batch_pages(sequential: bool, batch: u64, state: &mut u64) -> Vec<u64> {
+    if sequential {
+        let start = (batch * BATCH as u64) % FILE_PAGES;
+        return (start..start + BATCH as u64).collect();
+    }
+    (0..BATCH)
+        .map(|_| {
+            // xorshift keeps the random pattern identical across modes and runs
+            *state ^= *state << 13;
+            *state ^= *state >> 7;
+            *state ^= *state << 17;
+            *state % FILE_PAGES
+        })
+        .collect()
+}
+
+fn bench_page_io(c: &mut Criterion) {
+    let dir = tempfile::tempdir_in(std::env::temp_dir()).unwrap();
+    let modes = open_modes(dir.path());
+
+    let mut buffers: Vec<AlignedBuffer> = (0..BATCH)
+        .map(|i| {
+            let mut buffer = AlignedBuffer::page_aligned(PAGE_SIZE);
+            buffer.fill(i as u8);
+            buffer
+        })
+        .collect();
+
+    // Fill every file so reads hit allocated blocks
+    for (_, io) in &modes {
+        for start in (0..FILE_PAGES).step_by(BATCH) {
+            let requests: Vec<(u64, &[u8])> = buffers.iter().enumerate().map(|(i, b)| (start + i as u64, &b[..])).collect();
+            io.write_pages(&requests).unwrap();
+        }
+        io.sync().unwrap();
+    }
+
+    for (workload, sequential) in [("sequential", true), ("random", false)] {
+        let throughput = if sequential {
+            Throughput::Bytes((BATCH * PAGE_SIZE) as u64)
+        } else {
+            Throughput::Elements(BATCH as u64)
+        };
+
+        let mut group = c.benchmark_group(format!("page_write_{workload}"));
+        group.throughput(throughput.clone());
+        for (name, io) in &modes {
+            let (mut batch, mut state) = (0, 0x2545_f491_4f6c_dd1d_u64);
+            group.bench_function(*name, |b| {
+                b.iter(|| {
+                    let pages = batch_pages(sequential, batch, &mut state);
+                    batch += 1;
+                    let requests: Vec<(u64, &[u8])> = pages.iter().zip(&buffers).map(|(&page, b)| (page, &b[..])).collect();
+                    io.write_pages(&requests).unwrap();
+                })
+            });
+        }
+        group.finish();
+
+        let mut group = c.benchmark_group(format!("page_read_{workload}"));
+        group.throughput(throughput);
+        for (name, io) in &modes {
+            let (mut batch, mut state) = (0, 0x2545_f491_4f6c_dd1d_u64);
+            group.bench_function(*name, |b| {
+                b.iter(|| {
+                    let pages = batch_pages(sequential, batch, &mut state);
+                    batch += 1;
+                    let mut requests: Vec<(u64, &mut [u8])> = pages.iter().zip(buffers.iter_mut()).map(|(&page, b)| (page, &mut b[..])).collect();
+                    io.read_pages(&mut requests).unwrap();
+                })
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(page_io_benches, bench_page_io);
+criterion_main!(page_io_benches);
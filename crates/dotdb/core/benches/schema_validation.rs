@@ -0,0 +1,85 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Collection schema validation overhead
+//!
+//! Measures the latency schema validation adds to a document insert by
+//! comparing inserts into a collection with a schema, the same inserts with
+//! validation skipped, and inserts into a collection without a schema, plus
+//! validation on its own.
+
+use criterion::{BatchSize, Criterion, black_box, criterion_group, criterion_main};
+use dotdb_core::document::{JsonSchema, Validation, create_in_memory_collection_manager};
+use serde_json::{Value, json};
+
+const SCHEMA: &str = r#"{
+    "type": "object",
+    "required": ["name", "email", "age", "tags", "address"],
+    "additionalProperties": false,
+    "properties": {
+        "name": {"type": "string", "minLength": 1, "maxLength": 64},
+        "email": {"type": "string"},
+        "age": {"type": "integer", "minimum": 0, "maximum": 150},
+        "tags": {"type": "array", "items": {"type": "string"}, "maxItems": 16, "uniqueItems": true},
+        "address": {
+            "type": "object",
+            "required": ["city"],
+            "properties": {"city": {"type": "string"}, "zip": {"type": ["string", "null"]}}
+        }
+    }
+}"#;
+
+fn document(i: u64) -> Value {
+    json!({
+        "name": format!("user_{i}"),
+        "email": format!("user_{i}@example.com"),
+        "age": i % 100,
+        "tags": ["alpha", "beta", "gamma"],
+        "address": {"city": "Berlin", "zip": null}
+    })
+}
+
+fn bench_schema_validation(c: &mut Criterion) {
+    let schema = JsonSchema::from_json_str(SCHEMA).unwrap();
+    let sample = document(42);
+    c.bench_function("schema_validate_document", |b| b.iter(|| black_box(schema.validate(black_box(&sample)))));
+
+    // Every insert goes to a fresh store so the collection's growing document list does not skew later runs
+    let mut group = c.benchmark_group("insert_latency");
+    for (name, with_schema, validation) in [
+        ("no_schema", false, Validation::Enforce),
+        ("schema_enforced", true, Validation::Enforce),
+        ("schema_skipped", true, Validation::Skip),
+    ] {
+        group.bench_function(name, |b| {
+            b.iter_batched_ref(
+                || {
+                    let manager = create_in_memory_collection_manager().unwrap();
+                    if with_schema {
+                        manager.set_schema("users", SCHEMA, false).unwrap();
+                    }
+                    manager
+                },
+                |manager| black_box(manager.insert_value_with("users", document(7), validation).unwrap()),
+                BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(schema_benches, bench_schema_validation);
+criterion_main!(schema_benches);
@@ -0,0 +1,861 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::strategy::{CompactionConfig, CompactionStrategy, CompactionStrategyType, CompactionTask};
+use super::strategy::{LeveledStrategy, SizeTieredStrategy, TimeWindowStrategy};
+use crate::fs::FileMetadata;
+use crate::storage_engine::StorageConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// File the scheduler publishes its stats to, relative to the data directory
+pub const COMPACTION_STATUS_FILE: &str = "compaction_status.json";
+
+/// How often a paused compaction re-checks foreground write latency
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Longest the scheduler thread sleeps between status updates
+const SCHEDULER_TICK: Duration = Duration::from_secs(1);
+
+/// Most latency samples kept, regardless of the window
+const MAX_LATENCY_SAMPLES: usize = 4096;
+
+/// Background compaction scheduler configuration
+#[derive(Debug, Clone)]
+pub struct CompactionSchedulerConfig {
+    /// Strategy used to pick candidate files
+    pub strategy: CompactionStrategyType,
+    /// Thresholds for the strategy
+    pub strategy_config: CompactionConfig,
+    /// Number of dedicated compaction threads
+    pub threads: usize,
+    /// IO budget for compaction reads and writes combined (bytes per second, 0 for unlimited)
+    pub io_budget_bytes_per_sec: u64,
+    /// Window the IO budget is measured over
+    pub throttle_window: Duration,
+    /// Size of each read and write issued by a compaction
+    pub chunk_size: usize,
+    /// How often to look for compaction candidates
+    pub check_interval: Duration,
+    /// Pause compaction when p99 foreground write latency exceeds this
+    pub pause_write_latency: Duration,
+    /// Resume compaction once p99 foreground write latency drops below this
+    pub resume_write_latency: Duration,
+    /// Window foreground write latency is measured over
+    pub latency_window: Duration,
+    /// Where to publish stats for `dotdb compaction status`, if anywhere
+    pub status_path: Option<PathBuf>,
+}
+
+impl Default for CompactionSchedulerConfig {
+    fn default() -> Self {
+        Self {
+            strategy: CompactionStrategyType::SizeTiered,
+            strategy_config: CompactionConfig::default(),
+            threads: 1,
+            io_budget_bytes_per_sec: 20 * 1024 * 1024, // 20MB/s
+            throttle_window: Duration::from_secs(1),
+            chunk_size: 1024 * 1024, // 1MB
+            check_interval: Duration::from_secs(30),
+            pause_write_latency: Duration::from_millis(50),
+            resume_write_latency: Duration::from_millis(20),
+            latency_window: Duration::from_secs(10),
+            status_path: None,
+        }
+    }
+}
+
+impl CompactionSchedulerConfig {
+    /// Default configuration sized for the given storage engine settings.
+    /// Uses `compaction_threads` when set, otherwise `writer_threads`.
+    pub fn for_storage(config: &StorageConfig) -> Self {
+        Self {
+            threads: config.compaction_threads.unwrap_or(config.writer_threads).max(1),
+            io_budget_bytes_per_sec: config.compaction_io_budget_bytes_per_sec,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the IO budget in megabytes per second.
+    pub fn with_io_budget_mb(mut self, mb_per_sec: u64) -> Self {
+        self.io_budget_bytes_per_sec = mb_per_sec * 1024 * 1024;
+        self
+    }
+}
+
+/// Limits IO to a byte budget measured over a sliding window
+#[derive(Debug)]
+pub struct IoThrottle {
+    budget_bytes_per_sec: AtomicU64,
+    window: Duration,
+    history: Mutex<VecDeque<(Instant, u64)>>,
+}
+
+impl IoThrottle {
+    pub fn new(budget_bytes_per_sec: u64, window: Duration) -> Self {
+        Self {
+            budget_bytes_per_sec: AtomicU64::new(budget_bytes_per_sec),
+            window: window.max(Duration::from_millis(1)),
+            history: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Blocks until `bytes` more IO fits within the budget, then records it.
+    /// A single request larger than the whole window allowance is let through once the window is empty.
+    pub fn acquire(&self, bytes: u64) {
+        loop {
+            // Re-read on every attempt so a budget change applies to callers already waiting
+            let budget = self.budget_bytes_per_sec();
+            let allowance = (budget as f64 * self.window.as_secs_f64()) as u64;
+            let mut history = self.history.lock().unwrap();
+            let now = Instant::now();
+            self.expire(&mut history, now);
+
+            let used: u64 = history.iter().map(|(_, b)| b).sum();
+            if budget == 0 || history.is_empty() || used + bytes <= allowance {
+                history.push_back((now, bytes));
+                return;
+            }
+
+            // Wait for the oldest entry to leave the window
+            let oldest = history.front().map(|(at, _)| *at).unwrap_or(now);
+            let wait = (oldest + self.window).saturating_duration_since(now);
+            drop(history);
+            thread::sleep(wait.max(Duration::from_millis(1)));
+        }
+    }
+
+    /// Bytes per second recorded over the current window
+    pub fn throughput(&self) -> u64 {
+        let mut history = self.history.lock().unwrap();
+        self.expire(&mut history, Instant::now());
+        let used: u64 = history.iter().map(|(_, b)| b).sum();
+        (used as f64 / self.window.as_secs_f64()) as u64
+    }
+
+    pub fn budget_bytes_per_sec(&self) -> u64 {
+        self.budget_bytes_per_sec.load(Ordering::Relaxed)
+    }
+
+    /// Changes the budget, 0 for unlimited
+    pub fn set_budget_bytes_per_sec(&self, budget_bytes_per_sec: u64) {
+        self.budget_bytes_per_sec.store(budget_bytes_per_sec, Ordering::Relaxed);
+    }
+
+    fn expire(&self, history: &mut VecDeque<(Instant, u64)>, now: Instant) {
+        while let Some((at, _)) = history.front() {
+            if now.duration_since(*at) < self.window {
+                break;
+            }
+            history.pop_front();
+        }
+    }
+}
+
+/// Tracks foreground write latency so compaction can back off
+#[derive(Debug)]
+pub struct WriteLatencyMonitor {
+    window: Duration,
+    samples: Mutex<VecDeque<(Instant, Duration)>>,
+}
+
+impl WriteLatencyMonitor {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Records the latency of one foreground write.
+    pub fn record(&self, latency: Duration) {
+        let mut samples = self.samples.lock().unwrap();
+        samples.push_back((Instant::now(), latency));
+        if samples.len() > MAX_LATENCY_SAMPLES {
+            samples.pop_front();
+        }
+    }
+
+    /// 99th percentile latency over the window, or `None` without recent writes.
+    pub fn p99(&self) -> Option<Duration> {
+        let mut samples = self.samples.lock().unwrap();
+        let now = Instant::now();
+        while let Some((at, _)) = samples.front() {
+            if now.duration_since(*at) < self.window {
+                break;
+            }
+            samples.pop_front();
+        }
+
+        if samples.is_empty() {
+            return None;
+        }
+        let mut latencies: Vec<Duration> = samples.iter().map(|(_, l)| *l).collect();
+        latencies.sort_unstable();
+        let index = ((latencies.len() as f64 * 0.99).ceil() as usize).saturating_sub(1);
+        Some(latencies[index])
+    }
+}
+
+/// Supplies candidate files to the scheduler and compacts them
+pub trait CompactionSource: Send + Sync {
+    /// Returns the files that may be compacted.
+    fn candidate_files(&self) -> io::Result<Vec<FileMetadata>>;
+    /// Compacts the task's input files, doing all IO through `io`, and returns the files written.
+    fn compact(&self, task: &CompactionTask, io: &CompactionIo<'_>) -> io::Result<Vec<FileMetadata>>;
+}
+
+/// Throttled, pausable IO handed to a running compaction
+pub struct CompactionIo<'a> {
+    shared: &'a Shared,
+    task: &'a ActiveTask,
+}
+
+impl CompactionIo<'_> {
+    /// Reads into `buf` once the IO budget allows it.
+    pub fn read(&self, file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+        self.reserve(buf.len() as u64)?;
+        let n = file.read(buf)?;
+        self.task.bytes_read.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+
+    /// Fills `buf` from `file`, one chunk at a time as the IO budget allows.
+    pub fn read_exact(&self, file: &mut File, buf: &mut [u8]) -> io::Result<()> {
+        for chunk in buf.chunks_mut(self.chunk_size()) {
+            self.reserve(chunk.len() as u64)?;
+            file.read_exact(chunk)?;
+            self.task.bytes_read.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// Writes all of `buf` once the IO budget allows it.
+    pub fn write(&self, file: &mut File, buf: &[u8]) -> io::Result<()> {
+        self.reserve(buf.len() as u64)?;
+        file.write_all(buf)?;
+        self.task.bytes_written.fetch_add(buf.len() as u64, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Preferred size of each read and write
+    pub fn chunk_size(&self) -> usize {
+        self.shared.config.chunk_size.max(1)
+    }
+
+    fn reserve(&self, bytes: u64) -> io::Result<()> {
+        while self.shared.update_pause_state() {
+            if self.shared.shutdown.load(Ordering::Relaxed) {
+                break;
+            }
+            thread::sleep(PAUSE_POLL_INTERVAL);
+        }
+        if self.shared.shutdown.load(Ordering::Relaxed) {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "Compaction scheduler is shutting down"));
+        }
+        self.shared.throttle.acquire(bytes);
+        Ok(())
+    }
+}
+
+/// Scheduler state reported in stats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SchedulerState {
+    Running,
+    Paused,
+    Stopped,
+}
+
+/// Progress of a running compaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompactionProgress {
+    pub task_id: u64,
+    pub input_files: usize,
+    pub input_bytes: u64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    pub elapsed_ms: u64,
+}
+
+impl CompactionProgress {
+    /// Fraction of the input read so far, from 0.0 to 1.0
+    pub fn fraction_complete(&self) -> f64 {
+        if self.input_bytes == 0 { 1.0 } else { (self.bytes_read as f64 / self.input_bytes as f64).min(1.0) }
+    }
+}
+
+/// Snapshot of the scheduler's progress and throughput
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompactionSchedulerStats {
+    pub state: SchedulerState,
+    pub strategy: String,
+    pub threads: usize,
+    pub io_budget_bytes_per_sec: u64,
+    /// Compaction IO over the throttle window
+    pub throughput_bytes_per_sec: u64,
+    pub queued_tasks: usize,
+    pub active_tasks: Vec<CompactionProgress>,
+    pub completed_tasks: u64,
+    pub failed_tasks: u64,
+    pub files_compacted: u64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    pub pause_count: u64,
+    pub total_paused_ms: u64,
+    pub write_latency_p99_us: Option<u64>,
+    /// Unix time in seconds the snapshot was taken
+    pub updated_at: u64,
+}
+
+impl CompactionSchedulerStats {
+    /// Reads stats previously published by a running scheduler.
+    pub fn read_from(path: &Path) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        serde_json::from_slice(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+struct ActiveTask {
+    task: CompactionTask,
+    input_bytes: u64,
+    started_at: Instant,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+}
+
+#[derive(Default)]
+struct Counters {
+    completed_tasks: u64,
+    failed_tasks: u64,
+    files_compacted: u64,
+    bytes_read: u64,
+    bytes_written: u64,
+    pause_count: u64,
+    paused_since: Option<Instant>,
+    total_paused: Duration,
+}
+
+struct Shared {
+    config: CompactionSchedulerConfig,
+    throttle: Arc<IoThrottle>,
+    latency: Arc<WriteLatencyMonitor>,
+    shutdown: AtomicBool,
+    running: AtomicBool,
+    paused: AtomicBool,
+    queue: Mutex<VecDeque<CompactionTask>>,
+    queue_ready: Condvar,
+    /// IDs of files queued or being compacted, so overlapping tasks are not scheduled
+    claimed_files: Mutex<HashSet<u64>>,
+    active: Mutex<HashMap<u64, Arc<ActiveTask>>>,
+    counters: Mutex<Counters>,
+    next_task_id: AtomicU64,
+}
+
+impl Shared {
+    /// Re-evaluates foreground write latency and returns whether compaction should be paused.
+    fn update_pause_state(&self) -> bool {
+        let was_paused = self.paused.load(Ordering::Relaxed);
+        let should_pause = match self.latency.p99() {
+            Some(p99) if was_paused => p99 > self.config.resume_write_latency,
+            Some(p99) => p99 > self.config.pause_write_latency,
+            None => false,
+        };
+
+        if should_pause != was_paused && self.paused.compare_exchange(was_paused, should_pause, Ordering::AcqRel, Ordering::Relaxed).is_ok() {
+            let mut counters = self.counters.lock().unwrap();
+            if should_pause {
+                counters.pause_count += 1;
+                counters.paused_since = Some(Instant::now());
+                info!("Pausing compaction, foreground write latency is above {:?}", self.config.pause_write_latency);
+            } else {
+                if let Some(since) = counters.paused_since.take() {
+                    counters.total_paused += since.elapsed();
+                }
+                info!("Resuming compaction");
+            }
+        }
+        should_pause
+    }
+
+    fn stats(&self) -> CompactionSchedulerStats {
+        let paused = self.paused.load(Ordering::Relaxed);
+        let state = if !self.running.load(Ordering::Relaxed) {
+            SchedulerState::Stopped
+        } else if paused {
+            SchedulerState::Paused
+        } else {
+            SchedulerState::Running
+        };
+
+        let mut active_tasks: Vec<CompactionProgress> = self
+            .active
+            .lock()
+            .unwrap()
+            .values()
+            .map(|active| CompactionProgress {
+                task_id: active.task.id,
+                input_files: active.task.input_files.len(),
+                input_bytes: active.input_bytes,
+                bytes_read: active.bytes_read.load(Ordering::Relaxed),
+                bytes_written: active.bytes_written.load(Ordering::Relaxed),
+                elapsed_ms: active.started_at.elapsed().as_millis() as u64,
+            })
+            .collect();
+        active_tasks.sort_by_key(|p| p.task_id);
+
+        let counters = self.counters.lock().unwrap();
+        let current_pause = counters.paused_since.map(|since| since.elapsed()).unwrap_or_default();
+        let in_flight_read: u64 = active_tasks.iter().map(|p| p.bytes_read).sum();
+        let in_flight_written: u64 = active_tasks.iter().map(|p| p.bytes_written).sum();
+
+        CompactionSchedulerStats {
+            state,
+            strategy: format!("{:?}", self.config.strategy),
+            threads: self.config.threads,
+            io_budget_bytes_per_sec: self.throttle.budget_bytes_per_sec(),
+            throughput_bytes_per_sec: self.throttle.throughput(),
+            queued_tasks: self.queue.lock().unwrap().len(),
+            active_tasks,
+            completed_tasks: counters.completed_tasks,
+            failed_tasks: counters.failed_tasks,
+            files_compacted: counters.files_compacted,
+            bytes_read: counters.bytes_read + in_flight_read,
+            bytes_written: counters.bytes_written + in_flight_written,
+            pause_count: counters.pause_count,
+            total_paused_ms: (counters.total_paused + current_pause).as_millis() as u64,
+            write_latency_p99_us: self.latency.p99().map(|l| l.as_micros() as u64),
+            updated_at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+        }
+    }
+
+    fn publish_status(&self) {
+        let Some(path) = &self.config.status_path else {
+            return;
+        };
+        let result = serde_json::to_vec_pretty(&self.stats()).map_err(io::Error::other).and_then(|data| {
+            // Write then rename so readers never see a partial file
+            let tmp = path.with_extension("json.tmp");
+            fs::write(&tmp, data)?;
+            fs::rename(&tmp, path)
+        });
+        if let Err(e) = result {
+            warn!("Failed to publish compaction status to {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Runs compaction in the background on dedicated threads
+pub struct CompactionScheduler {
+    shared: Arc<Shared>,
+    strategy: Arc<dyn CompactionStrategy + Send + Sync>,
+    source: Arc<dyn CompactionSource>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl CompactionScheduler {
+    /// Creates a scheduler over the given source. Call [`start`](Self::start) to begin compacting.
+    pub fn new(config: CompactionSchedulerConfig, source: Arc<dyn CompactionSource>) -> Self {
+        let strategy_config = CompactionConfig {
+            strategy_type: config.strategy.clone(),
+            ..config.strategy_config.clone()
+        };
+        let strategy: Arc<dyn CompactionStrategy + Send + Sync> = match config.strategy {
+            CompactionStrategyType::Leveled => Arc::new(LeveledStrategy::new(strategy_config)),
+            CompactionStrategyType::TimeWindow => Arc::new(TimeWindowStrategy::new(strategy_config)),
+            // Custom strategies fall back to size-tiered, as in the compaction manager
+            CompactionStrategyType::SizeTiered | CompactionStrategyType::Custom => Arc::new(SizeTieredStrategy::new(strategy_config)),
+        };
+
+        let shared = Arc::new(Shared {
+            throttle: Arc::new(IoThrottle::new(config.io_budget_bytes_per_sec, config.throttle_window)),
+            latency: Arc::new(WriteLatencyMonitor::new(config.latency_window)),
+            shutdown: AtomicBool::new(false),
+            running: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            queue: Mutex::new(VecDeque::new()),
+            queue_ready: Condvar::new(),
+            claimed_files: Mutex::new(HashSet::new()),
+            active: Mutex::new(HashMap::new()),
+            counters: Mutex::new(Counters::default()),
+            next_task_id: AtomicU64::new(1),
+            config,
+        });
+
+        Self {
+            shared,
+            strategy,
+            source,
+            handles: Vec::new(),
+        }
+    }
+
+    /// Monitor the storage engine reports foreground write latency to.
+    pub fn latency_monitor(&self) -> Arc<WriteLatencyMonitor> {
+        Arc::clone(&self.shared.latency)
+    }
+
+    /// Throttle compaction IO goes through; its budget can be changed while the scheduler runs.
+    pub fn io_throttle(&self) -> Arc<IoThrottle> {
+        Arc::clone(&self.shared.throttle)
+    }
+
+    /// Starts the scheduler thread and the compaction worker threads.
+    pub fn start(&mut self) -> io::Result<()> {
+        if self.shared.running.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+        self.shared.shutdown.store(false, Ordering::Release);
+
+        for worker_id in 0..self.shared.config.threads.max(1) {
+            let shared = Arc::clone(&self.shared);
+            let source = Arc::clone(&self.source);
+            let handle = thread::Builder::new()
+                .name(format!("compaction-{worker_id}"))
+                .spawn(move || Self::worker_loop(&shared, source.as_ref()))?;
+            self.handles.push(handle);
+        }
+
+        let shared = Arc::clone(&self.shared);
+        let strategy = Arc::clone(&self.strategy);
+        let source = Arc::clone(&self.source);
+        let handle = thread::Builder::new().name("compaction-scheduler".to_string()).spawn(move || {
+            let mut last_check: Option<Instant> = None;
+            while !shared.shutdown.load(Ordering::Relaxed) {
+                if last_check.is_none_or(|at| at.elapsed() >= shared.config.check_interval) {
+                    Self::schedule(&shared, strategy.as_ref(), source.as_ref());
+                    last_check = Some(Instant::now());
+                }
+                shared.update_pause_state();
+                shared.publish_status();
+                // Woken early by `stop`
+                thread::park_timeout(SCHEDULER_TICK.min(shared.config.check_interval));
+            }
+        })?;
+        self.handles.push(handle);
+
+        info!("Compaction scheduler started with {} threads", self.shared.config.threads.max(1));
+        Ok(())
+    }
+
+    /// Signals all threads to stop and waits for them. Running compactions are abandoned at their next IO.
+    pub fn stop(&mut self) {
+        if !self.shared.running.load(Ordering::Acquire) {
+            return;
+        }
+        self.shared.shutdown.store(true, Ordering::Release);
+        self.shared.queue_ready.notify_all();
+        for handle in &self.handles {
+            handle.thread().unpark();
+        }
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+        self.shared.running.store(false, Ordering::Release);
+        self.shared.publish_status();
+    }
+
+    /// Looks for compaction candidates now instead of waiting for the next check. Returns the number of tasks queued.
+    pub fn trigger(&self) -> usize {
+        Self::schedule(&self.shared, self.strategy.as_ref(), self.source.as_ref())
+    }
+
+    /// Returns a snapshot of the scheduler's progress and throughput.
+    pub fn stats(&self) -> CompactionSchedulerStats {
+        self.shared.stats()
+    }
+
+    fn schedule(shared: &Shared, strategy: &(dyn CompactionStrategy + Send + Sync), source: &dyn CompactionSource) -> usize {
+        let files = match source.candidate_files() {
+            Ok(files) => files,
+            Err(e) => {
+                warn!("Failed to list compaction candidates: {}", e);
+                return 0;
+            }
+        };
+
+        let mut claimed = shared.claimed_files.lock().unwrap();
+        let available: Vec<FileMetadata> = files.into_iter().filter(|f| !claimed.contains(&f.id)).collect();
+        if !strategy.should_compact(&available) {
+            return 0;
+        }
+
+        let mut queued = 0;
+        let mut queue = shared.queue.lock().unwrap();
+        for mut task in strategy.select_files_for_compaction(&available) {
+            // Tasks from different strategy groups never share files, but guard against it anyway
+            if task.input_files.iter().any(|f| claimed.contains(&f.id)) {
+                continue;
+            }
+            claimed.extend(task.input_files.iter().map(|f| f.id));
+            task.id = shared.next_task_id.fetch_add(1, Ordering::Relaxed);
+            queue.push_back(task);
+            queued += 1;
+        }
+        drop(queue);
+
+        if queued > 0 {
+            shared.queue_ready.notify_all();
+        }
+        queued
+    }
+
+    fn worker_loop(shared: &Shared, source: &dyn CompactionSource) {
+        loop {
+            let task = {
+                let mut queue = shared.queue.lock().unwrap();
+                loop {
+                    if shared.shutdown.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    if let Some(task) = queue.pop_front() {
+                        break task;
+                    }
+                    queue = shared.queue_ready.wait_timeout(queue, SCHEDULER_TICK).unwrap().0;
+                }
+            };
+            Self::run_task(shared, source, task);
+        }
+    }
+
+    fn run_task(shared: &Shared, source: &dyn CompactionSource, task: CompactionTask) {
+        let active = Arc::new(ActiveTask {
+            input_bytes: task.input_files.iter().map(|f| f.size).sum(),
+            started_at: Instant::now(),
+            bytes_read: AtomicU64::new(0),
+            bytes_written: AtomicU64::new(0),
+            task,
+        });
+        shared.active.lock().unwrap().insert(active.task.id, Arc::clone(&active));
+
+        let io = CompactionIo { shared, task: &active };
+        let result = source.compact(&active.task, &io);
+
+        shared.active.lock().unwrap().remove(&active.task.id);
+        {
+            let mut counters = shared.counters.lock().unwrap();
+            counters.bytes_read += active.bytes_read.load(Ordering::Relaxed);
+            counters.bytes_written += active.bytes_written.load(Ordering::Relaxed);
+            match &result {
+                Ok(_) => {
+                    counters.completed_tasks += 1;
+                    counters.files_compacted += active.task.input_files.len() as u64;
+                }
+                Err(_) => counters.failed_tasks += 1,
+            }
+        }
+
+        let mut claimed = shared.claimed_files.lock().unwrap();
+        for file in &active.task.input_files {
+            claimed.remove(&file.id);
+        }
+        drop(claimed);
+
+        match result {
+            Ok(outputs) => info!(
+                "Compacted {} files into {} in {:?}",
+                active.task.input_files.len(),
+                outputs.len(),
+                active.started_at.elapsed()
+            ),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => info!("Compaction task {} interrupted", active.task.id),
+            Err(e) => warn!("Compaction task {} failed: {}", active.task.id, e),
+        }
+        shared.publish_status();
+    }
+}
+
+impl Drop for CompactionScheduler {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::{FileSystemLayout, FileType, LayoutConfig};
+    use tempfile::TempDir;
+
+    /// Concatenates its input files into a new one, enough to drive the scheduler
+    struct ConcatSource {
+        layout: FileSystemLayout,
+    }
+
+    impl CompactionSource for ConcatSource {
+        fn candidate_files(&self) -> io::Result<Vec<FileMetadata>> {
+            self.layout.list_files(FileType::Data)
+        }
+
+        fn compact(&self, task: &CompactionTask, io: &CompactionIo<'_>) -> io::Result<Vec<FileMetadata>> {
+            let id = self.layout.next_file_id(FileType::Data)?;
+            let path = self.layout.generate_file_path(FileType::Data, id, 2);
+            let mut output = File::create(&path)?;
+            let mut buf = vec![0u8; io.chunk_size()];
+            for input in &task.input_files {
+                let mut file = File::open(&input.path)?;
+                loop {
+                    let n = io.read(&mut file, &mut buf)?;
+                    if n == 0 {
+                        break;
+                    }
+                    io.write(&mut output, &buf[..n])?;
+                }
+                fs::remove_file(&input.path)?;
+            }
+            Ok(Vec::new())
+        }
+    }
+
+    fn create_test_layout() -> (FileSystemLayout, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let config = LayoutConfig {
+            base_path: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        (FileSystemLayout::new(config).unwrap(), temp_dir)
+    }
+
+    fn wait_for(mut condition: impl FnMut() -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if condition() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        false
+    }
+
+    #[test]
+    fn test_throttle_limits_rate() {
+        let throttle = IoThrottle::new(1000, Duration::from_millis(200));
+        let start = Instant::now();
+        // The window allows 200 bytes, so the third 100 byte request must wait for the first to expire
+        throttle.acquire(100);
+        throttle.acquire(100);
+        throttle.acquire(100);
+        assert!(start.elapsed() >= Duration::from_millis(150));
+    }
+
+    #[test]
+    fn test_unlimited_throttle_never_waits() {
+        let throttle = IoThrottle::new(0, Duration::from_secs(1));
+        let start = Instant::now();
+        for _ in 0..100 {
+            throttle.acquire(1024 * 1024);
+        }
+        assert!(start.elapsed() < Duration::from_millis(100));
+        assert!(throttle.throughput() > 0);
+    }
+
+    #[test]
+    fn test_latency_p99() {
+        let monitor = WriteLatencyMonitor::new(Duration::from_secs(10));
+        assert!(monitor.p99().is_none());
+
+        for _ in 0..99 {
+            monitor.record(Duration::from_millis(1));
+        }
+        monitor.record(Duration::from_millis(500));
+        assert_eq!(monitor.p99(), Some(Duration::from_millis(1)));
+
+        monitor.record(Duration::from_millis(500));
+        assert_eq!(monitor.p99(), Some(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn test_pauses_on_high_latency_and_resumes() {
+        let (layout, _dir) = create_test_layout();
+        let config = CompactionSchedulerConfig {
+            latency_window: Duration::from_millis(200),
+            ..Default::default()
+        };
+        let scheduler = CompactionScheduler::new(config, Arc::new(ConcatSource { layout }));
+        let monitor = scheduler.latency_monitor();
+
+        monitor.record(Duration::from_millis(100));
+        assert!(scheduler.shared.update_pause_state());
+        assert_eq!(scheduler.stats().pause_count, 1);
+
+        // Still above the resume threshold
+        monitor.record(Duration::from_millis(30));
+        assert!(scheduler.shared.update_pause_state());
+
+        // Once the slow writes age out of the window compaction resumes
+        thread::sleep(Duration::from_millis(250));
+        monitor.record(Duration::from_millis(1));
+        assert!(!scheduler.shared.update_pause_state());
+        assert!(scheduler.stats().total_paused_ms >= 200);
+    }
+
+    #[test]
+    fn test_background_compaction_runs_queued_tasks() {
+        let (layout, dir) = create_test_layout();
+        for id in 1..=3 {
+            let path = layout.generate_file_path(FileType::Data, id, 1);
+            fs::write(path, vec![id as u8; 4096]).unwrap();
+        }
+
+        let status_path = dir.path().join(COMPACTION_STATUS_FILE);
+        let config = CompactionSchedulerConfig {
+            chunk_size: 1024,
+            status_path: Some(status_path.clone()),
+            ..Default::default()
+        };
+        let source = Arc::new(ConcatSource {
+            layout: FileSystemLayout::new(LayoutConfig {
+                base_path: dir.path().to_path_buf(),
+                ..Default::default()
+            })
+            .unwrap(),
+        });
+        let mut scheduler = CompactionScheduler::new(config, source);
+        scheduler.start().unwrap();
+
+        assert!(wait_for(|| scheduler.stats().completed_tasks == 1));
+        let files = layout.list_files(FileType::Data).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].size, 3 * 4096);
+
+        let stats = scheduler.stats();
+        assert_eq!(stats.files_compacted, 3);
+        assert_eq!(stats.bytes_read, 3 * 4096);
+        assert_eq!(stats.bytes_written, 3 * 4096);
+
+        scheduler.stop();
+        let published = CompactionSchedulerStats::read_from(&status_path).unwrap();
+        assert_eq!(published.state, SchedulerState::Stopped);
+        assert_eq!(published.completed_tasks, 1);
+    }
+
+    #[test]
+    fn test_threads_follow_storage_config() {
+        let mut storage = StorageConfig::default();
+        assert_eq!(CompactionSchedulerConfig::for_storage(&storage).threads, storage.writer_threads);
+
+        storage.compaction_threads = Some(3);
+        assert_eq!(CompactionSchedulerConfig::for_storage(&storage).threads, 3);
+    }
+}
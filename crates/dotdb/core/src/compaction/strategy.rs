@@ -15,6 +15,7 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::fs::{FileMetadata, FileType};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 /// Represents the type of compaction strategy to be used.
@@ -345,6 +346,59 @@ impl CompactionStrategy for TimeWindowStrategy {
     }
 }
 
+/// Live and dead space of one segment of a key-value store's append log.
+///
+/// Overwriting or deleting a key leaves its old value in the segment it was
+/// written to; those dead entries take up space until the segment is compacted.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SegmentUsage {
+    pub segment: u32,
+    pub live_entries: u64,
+    pub live_bytes: u64,
+    /// Overwritten and deleted values still in the segment
+    pub dead_entries: u64,
+    pub dead_bytes: u64,
+}
+
+impl SegmentUsage {
+    /// Size of the segment on disk.
+    pub fn total_bytes(&self) -> u64 {
+        self.live_bytes + self.dead_bytes
+    }
+
+    /// Fraction of the segment taken by dead entries, from 0.0 to 1.0.
+    pub fn dead_ratio(&self) -> f64 {
+        if self.total_bytes() == 0 { 0.0 } else { self.dead_bytes as f64 / self.total_bytes() as f64 }
+    }
+}
+
+/// Picks the segments worth rewriting to reclaim the space of dead entries.
+#[derive(Debug, Clone)]
+pub struct DeadSpaceStrategy {
+    /// Segments with a smaller dead ratio are left alone
+    pub dead_ratio_threshold: f64,
+    /// Most segments rewritten by one compaction
+    pub max_segments: usize,
+}
+
+impl Default for DeadSpaceStrategy {
+    fn default() -> Self {
+        Self {
+            dead_ratio_threshold: 0.5,
+            max_segments: 64,
+        }
+    }
+}
+
+impl DeadSpaceStrategy {
+    /// Returns the segments whose dead ratio reaches the threshold, most garbage first.
+    pub fn select(&self, segments: &[SegmentUsage]) -> Vec<u32> {
+        let mut candidates: Vec<&SegmentUsage> = segments.iter().filter(|segment| segment.dead_bytes > 0 && segment.dead_ratio() >= self.dead_ratio_threshold).collect();
+        candidates.sort_by(|a, b| b.dead_ratio().total_cmp(&a.dead_ratio()).then(b.dead_bytes.cmp(&a.dead_bytes)));
+        candidates.into_iter().take(self.max_segments).map(|segment| segment.segment).collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -820,4 +874,24 @@ mod tests {
         // Should be capped at max_levels - 1
         assert_eq!(strategy.get_file_level(&huge_file), 1); // Level 1 (max_levels - 1)
     }
+
+    #[test]
+    fn test_dead_space_strategy_prefers_garbage() {
+        let segment = |segment, live_bytes, dead_bytes| SegmentUsage {
+            segment,
+            live_bytes,
+            dead_bytes,
+            ..Default::default()
+        };
+        let segments = [segment(0, 100, 900), segment(1, 900, 100), segment(2, 400, 600), segment(3, 0, 0), segment(4, 0, 50)];
+
+        let strategy = DeadSpaceStrategy::default();
+        assert_eq!(strategy.select(&segments), vec![4, 0, 2]);
+
+        let limited = DeadSpaceStrategy {
+            dead_ratio_threshold: 0.0,
+            max_segments: 2,
+        };
+        assert_eq!(limited.select(&segments), vec![4, 0]);
+    }
 }
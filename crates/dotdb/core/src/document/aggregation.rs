@@ -0,0 +1,740 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Aggregation queries
+//!
+//! An [`AggregationSpec`] groups a collection's documents by the values of some fields
+//! and, optionally, by a time window over a timestamp field, then computes `count`,
+//! `sum`, `min`, `max` and `avg` per group. Documents are folded into per-group
+//! accumulators one at a time, so memory grows with the number of groups rather than
+//! the number of documents.
+//!
+//! Windows are tumbling by default: each timestamp falls in exactly one window of the
+//! given size, aligned to the Unix epoch. With a `slide` shorter than the size, windows
+//! start every `slide` and overlap, so one document counts towards every window that
+//! contains it.
+//!
+//! Values that cannot be aggregated never fail the query. A non-numeric value given to
+//! a numeric function, an integer sum leaving the `i64` range or a float sum leaving the
+//! finite range is skipped and counted in [`AggregationWarnings`], as is a document
+//! without a usable timestamp when there is a window.
+
+use super::collection::compare_values;
+use super::projection::{ProjectedValue, Projection};
+use super::{DocumentError, DocumentResult};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+/// What to group documents by and what to compute for each group
+///
+/// In JSON, for the hourly order count and revenue per region:
+///
+/// ```json
+/// {
+///   "group_by": ["region"],
+///   "window": {"field": "created_at", "size": {"hours": 1}},
+///   "aggregates": [{"function": "count"}, {"function": "sum", "field": "total", "as": "revenue"}]
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AggregationSpec {
+    /// Paths whose values form the group key, e.g. `region` or `device.kind`
+    #[serde(default)]
+    pub group_by: Vec<String>,
+    /// Time window each group is further split by
+    #[serde(default)]
+    pub window: Option<TimeWindow>,
+    pub aggregates: Vec<Aggregate>,
+}
+
+/// Windows over a timestamp field
+///
+/// Timestamps are JSON numbers counted from the Unix epoch in `unit`; fractional
+/// timestamps are rounded down. Only documents in `[from, to)` are aggregated.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimeWindow {
+    /// Path of the timestamp
+    pub field: String,
+    #[serde(default)]
+    pub unit: TimeUnit,
+    pub size: WindowSize,
+    /// Distance between window starts; windows are tumbling without one
+    #[serde(default)]
+    pub slide: Option<WindowSize>,
+    /// First timestamp aggregated, in `unit`
+    #[serde(default)]
+    pub from: Option<i64>,
+    /// Timestamp aggregation stops before, in `unit`
+    #[serde(default)]
+    pub to: Option<i64>,
+}
+
+/// Unit timestamps are stored in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeUnit {
+    #[default]
+    Seconds,
+    Milliseconds,
+    Nanoseconds,
+}
+
+impl TimeUnit {
+    fn per_second(self) -> i64 {
+        match self {
+            Self::Seconds => 1,
+            Self::Milliseconds => 1_000,
+            Self::Nanoseconds => 1_000_000_000,
+        }
+    }
+}
+
+/// Length of a window, written `{"minutes": 5}` in JSON
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowSize {
+    Seconds(u64),
+    Minutes(u64),
+    Hours(u64),
+}
+
+impl WindowSize {
+    fn seconds(self) -> Option<u64> {
+        match self {
+            Self::Seconds(seconds) => Some(seconds),
+            Self::Minutes(minutes) => minutes.checked_mul(60),
+            Self::Hours(hours) => hours.checked_mul(3_600),
+        }
+    }
+
+    /// Length in `unit`, if it is positive and fits an `i64`
+    fn in_unit(self, unit: TimeUnit) -> Option<i64> {
+        let length = i64::try_from(self.seconds()?).ok()?.checked_mul(unit.per_second())?;
+        (length > 0).then_some(length)
+    }
+}
+
+impl fmt::Display for WindowSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Seconds(seconds) => write!(f, "{seconds}s"),
+            Self::Minutes(minutes) => write!(f, "{minutes}m"),
+            Self::Hours(hours) => write!(f, "{hours}h"),
+        }
+    }
+}
+
+/// One value computed per group
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Aggregate {
+    pub function: AggregateFunction,
+    /// Path aggregated; `count` without one counts documents
+    #[serde(default)]
+    pub field: Option<String>,
+    /// Name of the result, `function_field` (or `count`) by default
+    #[serde(default, rename = "as")]
+    pub alias: Option<String>,
+}
+
+impl Aggregate {
+    /// Name the result is reported under
+    pub fn name(&self) -> String {
+        match (&self.alias, &self.field) {
+            (Some(alias), _) => alias.clone(),
+            (None, Some(field)) => format!("{}_{field}", self.function),
+            (None, None) => self.function.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AggregateFunction {
+    /// Documents with a non-null value at the field, or all documents without a field
+    Count,
+    /// Integer sum while every value is an integer, float sum once one is not
+    Sum,
+    Min,
+    Max,
+    Avg,
+}
+
+impl fmt::Display for AggregateFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Count => "count",
+            Self::Sum => "sum",
+            Self::Min => "min",
+            Self::Max => "max",
+            Self::Avg => "avg",
+        })
+    }
+}
+
+/// Values skipped while aggregating
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AggregationWarnings {
+    /// Documents left out because the window field is missing or not a number
+    pub missing_timestamp: u64,
+    /// Values left out of `sum`, `min`, `max` or `avg` because they are not numbers
+    pub non_numeric: u64,
+    /// Values left out of `sum` or `avg` because adding them would overflow
+    pub overflow: u64,
+}
+
+impl AggregationWarnings {
+    /// Documents and values skipped in all
+    pub fn total(&self) -> u64 {
+        self.missing_timestamp + self.non_numeric + self.overflow
+    }
+}
+
+/// Results of one group
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AggregateGroup {
+    /// First timestamp of the group's window, in the window's unit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_start: Option<i64>,
+    /// Timestamp the group's window ends before
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_end: Option<i64>,
+    /// Value of each group-by path; `null` for documents without the path
+    pub keys: Map<String, Value>,
+    /// Each aggregate by name; `null` when the group had no value to aggregate
+    pub values: Map<String, Value>,
+}
+
+/// Outcome of an aggregation
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AggregationResult {
+    /// Groups ordered by window start, then by group-by values in document sort order
+    pub groups: Vec<AggregateGroup>,
+    /// Documents read
+    pub documents_scanned: u64,
+    /// Documents that counted towards at least one group
+    pub documents_aggregated: u64,
+    pub warnings: AggregationWarnings,
+}
+
+/// Window assignment of a validated [`TimeWindow`]
+#[derive(Debug, Clone, Copy)]
+struct Windows {
+    size: i64,
+    slide: i64,
+    from: Option<i64>,
+    to: Option<i64>,
+}
+
+impl Windows {
+    fn contains(&self, timestamp: i64) -> bool {
+        self.from.is_none_or(|from| timestamp >= from) && self.to.is_none_or(|to| timestamp < to)
+    }
+
+    /// Start of every window holding `timestamp`, earliest first
+    fn starts(&self, timestamp: i64) -> Vec<i64> {
+        let (size, slide, timestamp) = (self.size as i128, self.slide as i128, timestamp as i128);
+        let last = timestamp.div_euclid(slide) * slide;
+        // The earliest window holding the timestamp starts at the first multiple of the
+        // slide after `timestamp - size`
+        let first = (timestamp - size + slide).div_euclid(slide) * slide;
+        (0..)
+            .map(|step| first + step * slide)
+            .take_while(|start| *start <= last)
+            .filter_map(|start| i64::try_from(start).ok())
+            .collect()
+    }
+}
+
+/// Group key: window start, then the value of each group-by path
+#[derive(Debug, Clone, PartialEq)]
+struct GroupKey {
+    window: Option<i64>,
+    values: Vec<Value>,
+}
+
+impl Eq for GroupKey {}
+
+impl Ord for GroupKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.window.cmp(&other.window).then_with(|| {
+            self.values
+                .iter()
+                .zip(&other.values)
+                .map(|(a, b)| compare_values(a, b))
+                .find(|order| order.is_ne())
+                .unwrap_or(Ordering::Equal)
+        })
+    }
+}
+
+impl PartialOrd for GroupKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Running sum that stays exact while every value is an integer
+#[derive(Debug, Clone, Copy)]
+enum Sum {
+    Int(i64),
+    Float(f64),
+}
+
+impl Sum {
+    fn add(self, number: &serde_json::Number) -> Option<Self> {
+        match (self, number.as_i64()) {
+            (Self::Int(sum), Some(value)) => sum.checked_add(value).map(Self::Int),
+            // Integers past i64 would lose precision as floats, so they count as overflow
+            (Self::Int(_), None) if number.is_u64() => None,
+            (Self::Int(sum), None) => Self::finite(sum as f64 + number.as_f64()?),
+            (Self::Float(sum), _) => Self::finite(sum + number.as_f64()?),
+        }
+    }
+
+    fn finite(sum: f64) -> Option<Self> {
+        sum.is_finite().then_some(Self::Float(sum))
+    }
+
+    fn to_value(self) -> Value {
+        match self {
+            Self::Int(sum) => Value::from(sum),
+            Self::Float(sum) => Value::from(sum),
+        }
+    }
+}
+
+/// Running state of one aggregate in one group
+#[derive(Debug, Clone)]
+enum Accumulator {
+    Count(u64),
+    Sum(Option<Sum>),
+    Min(Option<Value>),
+    Max(Option<Value>),
+    Avg { sum: f64, count: u64 },
+}
+
+impl Accumulator {
+    fn new(function: AggregateFunction) -> Self {
+        match function {
+            AggregateFunction::Count => Self::Count(0),
+            AggregateFunction::Sum => Self::Sum(None),
+            AggregateFunction::Min => Self::Min(None),
+            AggregateFunction::Max => Self::Max(None),
+            AggregateFunction::Avg => Self::Avg { sum: 0.0, count: 0 },
+        }
+    }
+
+    /// Fold in the value at the aggregate's field, or `None` for a fieldless count
+    fn push(&mut self, value: Option<&Value>, warnings: &mut AggregationWarnings) {
+        let number = match (&mut *self, value) {
+            (Self::Count(count), None) => return *count += 1,
+            (_, Some(Value::Null)) => return,
+            (Self::Count(count), Some(_)) => return *count += 1,
+            (_, Some(Value::Number(number))) => number,
+            (_, Some(_)) => return warnings.non_numeric += 1,
+            (_, None) => return,
+        };
+
+        match self {
+            Self::Count(_) => {}
+            Self::Sum(sum) => match sum.unwrap_or(Sum::Int(0)).add(number) {
+                Some(added) => *sum = Some(added),
+                None => warnings.overflow += 1,
+            },
+            Self::Min(min) => {
+                let value = Value::Number(number.clone());
+                if min.as_ref().is_none_or(|min| compare_values(&value, min).is_lt()) {
+                    *min = Some(value);
+                }
+            }
+            Self::Max(max) => {
+                let value = Value::Number(number.clone());
+                if max.as_ref().is_none_or(|max| compare_values(&value, max).is_gt()) {
+                    *max = Some(value);
+                }
+            }
+            Self::Avg { sum, count } => match number.as_f64().map(|value| *sum + value).filter(|added| added.is_finite()) {
+                Some(added) => {
+                    *sum = added;
+                    *count += 1;
+                }
+                None => warnings.overflow += 1,
+            },
+        }
+    }
+
+    fn finish(self) -> Value {
+        match self {
+            Self::Count(count) => Value::from(count),
+            Self::Sum(sum) => sum.map_or(Value::Null, Sum::to_value),
+            Self::Min(value) | Self::Max(value) => value.unwrap_or(Value::Null),
+            Self::Avg { count: 0, .. } => Value::Null,
+            Self::Avg { sum, count } => Value::from(sum / count as f64),
+        }
+    }
+}
+
+/// A validated spec folding documents into groups
+pub(super) struct Aggregator {
+    spec: AggregationSpec,
+    names: Vec<String>,
+    projection: Projection,
+    /// Position in the projection of the window field, each group-by path and each
+    /// aggregate's field
+    window_position: Option<usize>,
+    group_positions: Vec<usize>,
+    aggregate_positions: Vec<Option<usize>>,
+    windows: Option<Windows>,
+    groups: BTreeMap<GroupKey, Vec<Accumulator>>,
+    result: AggregationResult,
+}
+
+impl Aggregator {
+    pub(super) fn new(spec: &AggregationSpec) -> DocumentResult<Self> {
+        if spec.aggregates.is_empty() {
+            return Err(DocumentError::InvalidAggregation("no aggregates requested".to_string()));
+        }
+
+        let mut names = Vec::with_capacity(spec.aggregates.len());
+        let mut seen = HashSet::new();
+        for aggregate in &spec.aggregates {
+            if aggregate.field.is_none() && aggregate.function != AggregateFunction::Count {
+                return Err(DocumentError::InvalidAggregation(format!("{} needs a field", aggregate.function)));
+            }
+            let name = aggregate.name();
+            if !seen.insert(name.clone()) {
+                return Err(DocumentError::InvalidAggregation(format!("more than one aggregate is named '{name}'")));
+            }
+            names.push(name);
+        }
+
+        let windows = spec.window.as_ref().map(validate_window).transpose()?;
+
+        let mut paths: Vec<&str> = spec.window.iter().map(|window| window.field.as_str()).collect();
+        paths.extend(spec.group_by.iter().map(String::as_str));
+        paths.extend(spec.aggregates.iter().filter_map(|aggregate| aggregate.field.as_deref()));
+        let projection = Projection::new(&paths).map_err(|e| match e {
+            DocumentError::InvalidProjection(message) => DocumentError::InvalidAggregation(message),
+            e => e,
+        })?;
+        let position = |path: &str| projection.paths().position(|projected| projected == path.trim());
+
+        Ok(Self {
+            names,
+            window_position: spec.window.as_ref().and_then(|window| position(&window.field)),
+            group_positions: spec.group_by.iter().filter_map(|path| position(path)).collect(),
+            aggregate_positions: spec.aggregates.iter().map(|aggregate| aggregate.field.as_deref().and_then(position)).collect(),
+            projection,
+            windows,
+            groups: BTreeMap::new(),
+            result: AggregationResult::default(),
+            spec: spec.clone(),
+        })
+    }
+
+    /// Time range documents must fall in, as `(from, to)` with `to` exclusive
+    pub(super) fn range(&self) -> Option<(Option<i64>, Option<i64>)> {
+        self.windows.map(|windows| (windows.from, windows.to))
+    }
+
+    /// Fold one document into its groups
+    pub(super) fn push(&mut self, content: &Value) {
+        self.result.documents_scanned += 1;
+        let values = self.projection.project(content);
+
+        let windows: Vec<Option<i64>> = match (self.windows, self.window_position) {
+            (Some(windows), Some(position)) => match timestamp(&values[position]) {
+                Some(timestamp) if windows.contains(timestamp) => windows.starts(timestamp).into_iter().map(Some).collect(),
+                Some(_) => return,
+                None => return self.result.warnings.missing_timestamp += 1,
+            },
+            _ => vec![None],
+        };
+        self.result.documents_aggregated += 1;
+
+        let keys: Vec<Value> = self
+            .group_positions
+            .iter()
+            .map(|&position| match &values[position] {
+                ProjectedValue::Present(value) => value.clone(),
+                ProjectedValue::Missing => Value::Null,
+            })
+            .collect();
+
+        for window in windows {
+            let key = GroupKey { window, values: keys.clone() };
+            let accumulators = self
+                .groups
+                .entry(key)
+                .or_insert_with(|| self.spec.aggregates.iter().map(|aggregate| Accumulator::new(aggregate.function)).collect());
+            for (accumulator, position) in accumulators.iter_mut().zip(&self.aggregate_positions) {
+                let value = position.map(|position| match &values[position] {
+                    ProjectedValue::Present(value) => value,
+                    ProjectedValue::Missing => &Value::Null,
+                });
+                accumulator.push(value, &mut self.result.warnings);
+            }
+        }
+    }
+
+    pub(super) fn finish(mut self) -> AggregationResult {
+        let size = self.windows.map(|windows| windows.size);
+        self.result.groups = self
+            .groups
+            .into_iter()
+            .map(|(key, accumulators)| AggregateGroup {
+                window_start: key.window,
+                window_end: key.window.zip(size).map(|(start, size)| start.saturating_add(size)),
+                keys: self.spec.group_by.iter().map(|path| path.trim().to_string()).zip(key.values).collect(),
+                values: self.names.iter().cloned().zip(accumulators.into_iter().map(Accumulator::finish)).collect(),
+            })
+            .collect();
+        self.result
+    }
+}
+
+fn validate_window(window: &TimeWindow) -> DocumentResult<Windows> {
+    let invalid = |message: String| DocumentError::InvalidAggregation(message);
+    let size = window
+        .size
+        .in_unit(window.unit)
+        .ok_or_else(|| invalid(format!("window size {} must be positive and fit in {:?}", window.size, window.unit)))?;
+    let slide = match window.slide {
+        Some(slide) => slide
+            .in_unit(window.unit)
+            .ok_or_else(|| invalid(format!("window slide {slide} must be positive and fit in {:?}", window.unit)))?,
+        None => size,
+    };
+    if slide > size {
+        return Err(invalid(format!("window slide {} is longer than the window size {}", window.slide.unwrap_or(window.size), window.size)));
+    }
+    if let (Some(from), Some(to)) = (window.from, window.to)
+        && from >= to
+    {
+        return Err(invalid(format!("window range from {from} to {to} is empty")));
+    }
+    Ok(Windows {
+        size,
+        slide,
+        from: window.from,
+        to: window.to,
+    })
+}
+
+/// A projected timestamp as a whole number of its unit
+pub(super) fn timestamp(value: &ProjectedValue) -> Option<i64> {
+    match value {
+        ProjectedValue::Present(Value::Number(number)) => number.as_i64().or_else(|| number.as_f64().filter(|value| value.is_finite()).map(|value| value.floor() as i64)),
+        _ => None,
+    }
+}
+
+/// Key bytes of a timestamp that sort in timestamp order, as
+/// [`build_time_index`](super::CollectionManager::build_time_index) encodes them
+pub fn timestamp_key(timestamp: i64) -> Vec<u8> {
+    ((timestamp as u64) ^ (1 << 63)).to_be_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::collection::{CollectionManager, create_in_memory_collection_manager};
+    use serde_json::json;
+
+    fn spec(value: Value) -> AggregationSpec {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn events() -> CollectionManager {
+        let manager = create_in_memory_collection_manager().unwrap();
+        for (ts, region, latency) in [(0, "eu", 10), (30, "us", 20), (59, "eu", 30), (60, "eu", 40), (125, "us", 50), (130, "us", 60)] {
+            manager.insert_value("events", json!({"ts": ts, "region": region, "latency": latency})).unwrap();
+        }
+        manager
+    }
+
+    #[test]
+    fn test_tumbling_windows_grouped_by_field() {
+        let manager = events();
+        let result = manager
+            .aggregate(
+                "events",
+                &spec(json!({
+                    "group_by": ["region"],
+                    "window": {"field": "ts", "size": {"minutes": 1}},
+                    "aggregates": [
+                        {"function": "count"},
+                        {"function": "sum", "field": "latency"},
+                        {"function": "avg", "field": "latency", "as": "mean"},
+                        {"function": "min", "field": "latency"},
+                        {"function": "max", "field": "latency"}
+                    ]
+                })),
+            )
+            .unwrap();
+
+        let summary: Vec<(i64, i64, &Value, Value)> = result
+            .groups
+            .iter()
+            .map(|group| (group.window_start.unwrap(), group.window_end.unwrap(), &group.keys["region"], Value::Object(group.values.clone())))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (0, 60, &json!("eu"), json!({"count": 2, "sum_latency": 40, "mean": 20.0, "min_latency": 10, "max_latency": 30})),
+                (0, 60, &json!("us"), json!({"count": 1, "sum_latency": 20, "mean": 20.0, "min_latency": 20, "max_latency": 20})),
+                (60, 120, &json!("eu"), json!({"count": 1, "sum_latency": 40, "mean": 40.0, "min_latency": 40, "max_latency": 40})),
+                (120, 180, &json!("us"), json!({"count": 2, "sum_latency": 110, "mean": 55.0, "min_latency": 50, "max_latency": 60})),
+            ]
+        );
+        assert_eq!((result.documents_scanned, result.documents_aggregated), (6, 6));
+        assert_eq!(result.warnings, AggregationWarnings::default());
+    }
+
+    #[test]
+    fn test_sliding_windows_and_range() {
+        let manager = events();
+        let result = manager
+            .aggregate(
+                "events",
+                &spec(json!({
+                    "window": {"field": "ts", "size": {"seconds": 60}, "slide": {"seconds": 30}, "from": 30, "to": 130},
+                    "aggregates": [{"function": "count"}]
+                })),
+            )
+            .unwrap();
+
+        // 30, 59, 60 and 125 are in range; each falls in two overlapping windows
+        let counts: Vec<(i64, &Value)> = result.groups.iter().map(|group| (group.window_start.unwrap(), &group.values["count"])).collect();
+        assert_eq!(counts, vec![(0, &json!(2)), (30, &json!(3)), (60, &json!(1)), (90, &json!(1)), (120, &json!(1))]);
+        assert_eq!((result.documents_scanned, result.documents_aggregated), (6, 4));
+
+        let windows = Windows {
+            size: 60,
+            slide: 30,
+            from: None,
+            to: None,
+        };
+        assert_eq!(windows.starts(-1), vec![-60, -30]);
+        assert_eq!(windows.starts(30), vec![0, 30]);
+    }
+
+    #[test]
+    fn test_unaggregatable_values_are_skipped_and_counted() {
+        let manager = create_in_memory_collection_manager().unwrap();
+        for document in [
+            json!({"ts": 1, "n": i64::MAX}),
+            json!({"ts": 2, "n": 1}),
+            json!({"ts": 3, "n": "seven"}),
+            json!({"ts": 4, "n": null}),
+            json!({"ts": "yesterday", "n": 5}),
+            json!({"n": 6}),
+        ] {
+            manager.insert_value("samples", document).unwrap();
+        }
+
+        let result = manager
+            .aggregate(
+                "samples",
+                &spec(json!({
+                    "window": {"field": "ts", "unit": "milliseconds", "size": {"hours": 1}},
+                    "aggregates": [{"function": "sum", "field": "n"}, {"function": "count", "field": "n"}, {"function": "max", "field": "n"}]
+                })),
+            )
+            .unwrap();
+
+        assert_eq!(result.groups.len(), 1);
+        assert_eq!(result.groups[0].window_end, Some(3_600_000));
+        // The sum keeps the first value and skips the one that would overflow it
+        assert_eq!(Value::Object(result.groups[0].values.clone()), json!({"sum_n": i64::MAX, "count_n": 3, "max_n": i64::MAX}));
+        assert_eq!(
+            result.warnings,
+            AggregationWarnings {
+                missing_timestamp: 2,
+                non_numeric: 2,
+                overflow: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_mixed_numbers_and_missing_group_values() {
+        let manager = create_in_memory_collection_manager().unwrap();
+        for document in [json!({"kind": "a", "n": 1}), json!({"kind": "a", "n": 2.5}), json!({"n": 4}), json!({"kind": 7, "n": 1})] {
+            manager.insert_value("samples", document).unwrap();
+        }
+
+        let result = manager
+            .aggregate("samples", &spec(json!({"group_by": ["kind"], "aggregates": [{"function": "sum", "field": "n"}]})))
+            .unwrap();
+        let sums: Vec<(&Value, &Value)> = result.groups.iter().map(|group| (&group.keys["kind"], &group.values["sum_n"])).collect();
+        // Missing values group under null, which sorts before numbers and strings
+        assert_eq!(sums, vec![(&json!(null), &json!(4)), (&json!(7), &json!(1)), (&json!("a"), &json!(3.5))]);
+    }
+
+    #[test]
+    fn test_indexed_aggregation_reads_only_the_range() {
+        let manager = create_in_memory_collection_manager().unwrap();
+        for ts in -50..150 {
+            manager.insert_value("events", json!({"ts": ts, "n": 1})).unwrap();
+        }
+        let index = manager.build_time_index("events", "ts", 16, 0.9).unwrap();
+
+        let ranged = spec(json!({
+            "window": {"field": "ts", "size": {"seconds": 10}, "from": -15, "to": 25},
+            "aggregates": [{"function": "sum", "field": "n"}]
+        }));
+        let indexed = manager.aggregate_indexed("events", &ranged, &index).unwrap();
+        assert_eq!(indexed.documents_scanned, 40);
+        assert_eq!(indexed.groups.iter().map(|group| group.window_start.unwrap()).collect::<Vec<_>>(), vec![-20, -10, 0, 10, 20]);
+        assert_eq!(indexed.groups[0].values["sum_n"], json!(5));
+
+        let scanned = manager.aggregate("events", &ranged).unwrap();
+        assert_eq!(scanned.documents_scanned, 200);
+        assert_eq!(scanned.groups, indexed.groups);
+
+        let unwindowed = spec(json!({"aggregates": [{"function": "count"}]}));
+        assert!(matches!(manager.aggregate_indexed("events", &unwindowed, &index), Err(DocumentError::InvalidAggregation(_))));
+    }
+
+    #[test]
+    fn test_invalid_specs_are_rejected() {
+        let manager = events();
+        let invalid = [
+            json!({"aggregates": []}),
+            json!({"aggregates": [{"function": "sum"}]}),
+            json!({"aggregates": [{"function": "count"}, {"function": "sum", "field": "n", "as": "count"}]}),
+            json!({"window": {"field": "ts", "size": {"seconds": 0}}, "aggregates": [{"function": "count"}]}),
+            json!({"window": {"field": "ts", "size": {"seconds": 10}, "slide": {"minutes": 1}}, "aggregates": [{"function": "count"}]}),
+            json!({"window": {"field": "ts", "unit": "nanoseconds", "size": {"hours": u64::MAX / 4}}, "aggregates": [{"function": "count"}]}),
+            json!({"window": {"field": "ts", "size": {"seconds": 10}, "from": 5, "to": 5}, "aggregates": [{"function": "count"}]}),
+            json!({"group_by": ["a..b"], "aggregates": [{"function": "count"}]}),
+        ];
+        for value in invalid {
+            assert!(
+                matches!(manager.aggregate("events", &spec(value.clone())), Err(DocumentError::InvalidAggregation(_))),
+                "{value} was accepted"
+            );
+        }
+        assert!(serde_json::from_value::<AggregationSpec>(json!({"aggregates": [{"function": "median", "field": "n"}]})).is_err());
+    }
+}
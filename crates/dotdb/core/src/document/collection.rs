@@ -19,33 +19,260 @@
 //! This module provides high-level collection management operations
 //! for organizing documents in the document store.
 
-use super::{CollectionName, Document, DocumentId, DocumentResult, DocumentStorage};
+use super::aggregation::{self, AggregationResult, AggregationSpec, Aggregator};
+use super::compression::{CompressionCodec, RecompressionReport};
+use super::projection::{ProjectedDocument, ProjectedValue, Projection};
+use super::read_snapshot::ReadSnapshot;
+use super::schema::{DocumentViolations, JsonSchema, SchemaReport, Validation};
+use super::transaction::{DocumentTransaction, TransactionRegistry};
+use super::{CollectionName, Document, DocumentCompaction, DocumentError, DocumentId, DocumentResult, DocumentStorage, DocumentWrite, Namespace};
+use crate::compaction::strategy::DeadSpaceStrategy;
+use crate::indices::{BPlusTree, CompositeKey, RangeQuery};
+use crate::statistics::{
+    AnalyzeSource, CollectionSpaceUsage, FieldAccess, FieldAccessKind, IndexAdvisor, IndexAdvisorConfig, IndexRecommendation, MutationKind, MutationRecorder, StatisticsCollector, StatisticsError,
+    StatisticsResult,
+};
+use crate::storage_engine::IsolationLevel;
+use serde::Serialize;
 use serde_json::Value;
-use std::sync::Arc;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tracing::warn;
+
+/// Documents a compression rewrite stores again at a time, holding writers back while it does
+const REWRITE_BATCH_SIZE: usize = 256;
+
+/// Documents a collection copy writes in one commit, reporting progress after each
+const COPY_BATCH_SIZE: usize = 256;
+
+/// Time a read snapshot may be held before it is released by force
+pub const DEFAULT_SNAPSHOT_MAX_AGE: Duration = Duration::from_secs(600);
+
+/// Compiled schemas by qualified collection name; `None` records that a collection has no schema
+type SchemaCache = HashMap<String, Option<Arc<JsonSchema>>>;
+
+/// Order and window of a collection scan
+///
+/// Documents are ordered by the value at `sort_field`, with documents that lack it first,
+/// or by ID when there is no sort field. Ties are broken by document ID so pages of the
+/// same scan neither repeat nor skip documents. `descending` reverses the whole order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanOptions {
+    /// Path to order by, e.g. `age` or `address.city`
+    pub sort_field: Option<String>,
+    pub descending: bool,
+    /// Most documents to return; `None` returns every document after `offset`
+    pub limit: Option<usize>,
+    /// Documents to skip before the first one returned
+    pub offset: usize,
+}
+
+/// What a collection copy does with the source's covering indexes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IndexCopy {
+    /// The destination has no covering indexes
+    #[default]
+    Skip,
+    /// The destination gets the same indexes before documents are copied, each kept
+    /// current as documents are written
+    Copy,
+    /// The same indexes are built on the destination once every document is copied
+    Rebuild,
+}
+
+/// Which documents a collection copy takes and what it copies with them
+///
+/// The source's schema and codec always come along.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CopyOptions {
+    /// Copy only the documents whose top-level field has this value, matched like
+    /// [`CollectionManager::find_by_field`]
+    pub filter: Option<(String, Value)>,
+    pub indexes: IndexCopy,
+}
+
+/// What a collection copy did
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CopyReport {
+    /// Snapshot the source was read at, in nanoseconds since the Unix epoch; writes to
+    /// the source after it are not copied and can be replayed from it
+    pub snapshot: u64,
+    /// Documents the source held at the snapshot
+    pub documents_total: u64,
+    pub documents_copied: u64,
+    /// Documents left out by the filter
+    pub documents_skipped: u64,
+    /// Paths of each covering index copied or rebuilt on the destination
+    pub indexes: Vec<Vec<String>>,
+}
 
 /// Collection manager for high-level document operations
+///
+/// Collection operations are confined to the manager's namespace. Handles for other
+/// namespaces come from [`with_namespace`](Self::with_namespace) and share the
+/// storage, transaction locks, index advisor and schema cache of the manager they came from.
+///
+/// Collections with a schema (see [`set_schema`](Self::set_schema)) reject writes of
+/// documents that do not match it. Compiled schemas are cached, so schema changes made
+/// through a manager that does not share this one's cache are not seen until restart.
 pub struct CollectionManager {
     storage: Arc<dyn DocumentStorage>,
+    transactions: Arc<TransactionRegistry>,
+    advisor: Arc<Mutex<IndexAdvisor>>,
+    schemas: Arc<RwLock<SchemaCache>>,
+    snapshot_max_age: Duration,
+    statistics: Option<MutationRecorder>,
 }
 
 impl CollectionManager {
-    /// Create a new collection manager
+    /// Create a new collection manager for the storage's namespace
     pub fn new(storage: Arc<dyn DocumentStorage>) -> Self {
-        Self { storage }
+        Self {
+            storage,
+            transactions: Arc::new(TransactionRegistry::new()),
+            advisor: Arc::new(Mutex::new(IndexAdvisor::new(IndexAdvisorConfig::default()))),
+            schemas: Arc::new(RwLock::new(HashMap::new())),
+            snapshot_max_age: DEFAULT_SNAPSHOT_MAX_AGE,
+            statistics: None,
+        }
+    }
+
+    /// Use an index advisor with `config`, reloading its persisted window if there is one
+    pub fn with_index_advisor(mut self, config: IndexAdvisorConfig) -> DocumentResult<Self> {
+        self.advisor = Arc::new(Mutex::new(IndexAdvisor::open(config)?));
+        Ok(self)
+    }
+
+    /// Count this manager's writes in `collector` and re-analyze collections in the
+    /// background as they change
+    ///
+    /// Must be called within a tokio runtime. Returns the worker counting the writes,
+    /// which finishes once this manager and every handle sharing its statistics are dropped.
+    pub fn with_statistics(mut self, collector: &Arc<StatisticsCollector>) -> (Self, tokio::task::JoinHandle<()>) {
+        // The refresh samples through a handle that reports nothing, so it does not keep the recorder alive
+        collector.start_auto_refresh(Arc::new(self.share()));
+        let (recorder, worker) = collector.mutation_recorder();
+        self.statistics = Some(recorder);
+        (self, worker)
+    }
+
+    /// Release read snapshots held longer than `max_age` by force
+    pub fn with_snapshot_max_age(mut self, max_age: Duration) -> Self {
+        self.snapshot_max_age = max_age;
+        self
+    }
+
+    /// Time a read snapshot may be held before it is released by force
+    pub fn snapshot_max_age(&self) -> Duration {
+        self.snapshot_max_age
+    }
+
+    /// A handle whose collection operations are confined to `namespace`
+    pub fn with_namespace(&self, namespace: &str) -> DocumentResult<Self> {
+        let namespace = Namespace::new(namespace)?;
+        Ok(Self {
+            storage: self.storage.in_namespace(&namespace),
+            ..self.share()
+        })
+    }
+
+    /// Another handle on this manager's namespace, sharing everything with it
+    fn share(&self) -> Self {
+        Self {
+            storage: self.storage.clone(),
+            transactions: self.transactions.clone(),
+            advisor: self.advisor.clone(),
+            schemas: self.schemas.clone(),
+            snapshot_max_age: self.snapshot_max_age,
+            statistics: self.statistics.clone(),
+        }
+    }
+
+    /// Namespace this manager's collections live in
+    pub fn namespace(&self) -> &str {
+        self.storage.namespace().as_str()
+    }
+
+    /// Create a namespace, returning whether it was created
+    pub fn create_namespace(&self, namespace: &str) -> DocumentResult<bool> {
+        self.storage.create_namespace(&Namespace::new(namespace)?)
+    }
+
+    /// List all namespaces
+    pub fn list_namespaces(&self) -> DocumentResult<Vec<String>> {
+        let namespaces = self.storage.list_namespaces()?;
+        Ok(namespaces.into_iter().map(|ns| ns.as_str().to_string()).collect())
+    }
+
+    /// Drop a namespace, returning whether it existed
+    ///
+    /// Fails with [`NamespaceNotEmpty`](super::DocumentError::NamespaceNotEmpty) while the
+    /// namespace has collections unless `force` is set, in which case they are deleted too.
+    pub fn drop_namespace(&self, namespace: &str, force: bool) -> DocumentResult<bool> {
+        let dropped = self.storage.drop_namespace(&Namespace::new(namespace)?, force)?;
+        self.schemas.write().unwrap().clear();
+        Ok(dropped)
+    }
+
+    /// Name the index advisor tracks a collection under; collections outside the
+    /// default namespace are qualified so tenants' statistics stay apart
+    fn advisor_collection<'a>(&self, collection: &'a str) -> Cow<'a, str> {
+        let namespace = self.storage.namespace();
+        if namespace.is_default() {
+            Cow::Borrowed(collection)
+        } else {
+            Cow::Owned(format!("{namespace}.{collection}"))
+        }
+    }
+
+    /// Count `count` writes of `kind` to a collection in the statistics, if they are collected
+    fn record_mutation(&self, collection: &str, kind: MutationKind, count: u64) {
+        if let Some(statistics) = &self.statistics {
+            statistics.record(&self.advisor_collection(collection), kind, count);
+        }
+    }
+
+    /// Begin a transaction at the given isolation level
+    ///
+    /// See the [`transaction`](super::transaction) module for what each level guarantees.
+    pub fn begin_transaction(&self, isolation_level: IsolationLevel) -> DocumentTransaction {
+        DocumentTransaction::begin(self.storage.clone(), self.transactions.clone(), isolation_level)
+    }
+
+    /// Pin a consistent snapshot of every collection for reads that span several of them
+    ///
+    /// Every read through the returned handle sees the documents as they were when it
+    /// began, whatever is written since. The history it needs is kept until the handle is
+    /// released, or released by force once it has been held longer than the
+    /// [snapshot maximum age](Self::with_snapshot_max_age).
+    pub fn begin_read_snapshot(&self) -> DocumentResult<ReadSnapshot> {
+        let pin = self.storage.pin_snapshot(self.snapshot_max_age)?;
+        Ok(ReadSnapshot::new(self.share(), pin))
     }
 
     /// Insert a JSON document into a collection
     pub fn insert_json(&self, collection: &str, json: &str) -> DocumentResult<DocumentId> {
-        let collection_name = CollectionName::new(collection);
-        let document = Document::from_json_string(json)?;
-        self.storage.create_document(&collection_name, document)
+        self.insert_value_with(collection, serde_json::from_str(json)?, Validation::Enforce)
     }
 
     /// Insert a JSON value into a collection
     pub fn insert_value(&self, collection: &str, value: Value) -> DocumentResult<DocumentId> {
+        self.insert_value_with(collection, value, Validation::Enforce)
+    }
+
+    /// Insert a JSON value into a collection, checking it against the collection's schema
+    /// unless `validation` is [`Validation::Skip`]
+    pub fn insert_value_with(&self, collection: &str, value: Value, validation: Validation) -> DocumentResult<DocumentId> {
         let collection_name = CollectionName::new(collection);
+        self.validate(&collection_name, &value, validation)?;
         let document = Document::new(value);
-        self.storage.create_document(&collection_name, document)
+        self.advisor.lock().unwrap().record_write(&self.advisor_collection(collection));
+        let id = self.storage.create_document(&collection_name, document)?;
+        self.record_mutation(collection, MutationKind::Insert, 1);
+        Ok(id)
     }
 
     /// Get a document as JSON string
@@ -66,25 +293,146 @@ impl CollectionManager {
         }
     }
 
+    /// Get a document as JSON value with its version, for a later
+    /// [`update_value_if_version`](Self::update_value_if_version)
+    pub fn get_value_with_version(&self, collection: &str, id: &DocumentId) -> DocumentResult<Option<(Value, u64)>> {
+        let collection_name = CollectionName::new(collection);
+        Ok(self.storage.get_document(&collection_name, id)?.map(|document| (document.content, document.metadata.version)))
+    }
+
+    /// Get a document as JSON string as it was at `timestamp`, in nanoseconds since the
+    /// Unix epoch
+    pub fn get_json_as_of(&self, collection: &str, id: &DocumentId, timestamp: u64) -> DocumentResult<Option<String>> {
+        match self.get_document_as_of(collection, id, timestamp)? {
+            Some(document) => Ok(Some(document.to_json_string()?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Get a document as JSON value as it was at `timestamp`, in nanoseconds since the
+    /// Unix epoch
+    pub fn get_value_as_of(&self, collection: &str, id: &DocumentId, timestamp: u64) -> DocumentResult<Option<Value>> {
+        Ok(self.get_document_as_of(collection, id, timestamp)?.map(|document| document.content))
+    }
+
+    fn get_document_as_of(&self, collection: &str, id: &DocumentId, timestamp: u64) -> DocumentResult<Option<Document>> {
+        let snapshot = self.storage.read_snapshot(timestamp)?;
+        self.storage.get_document_as_of(&CollectionName::new(collection), id, snapshot)
+    }
+
+    /// IDs of the collection's documents, at `snapshot` if given
+    pub(super) fn document_ids(&self, collection: &CollectionName, snapshot: Option<u64>) -> DocumentResult<Vec<DocumentId>> {
+        match snapshot {
+            Some(snapshot) => self.storage.list_documents_as_of(collection, snapshot),
+            None => self.storage.list_documents(collection),
+        }
+    }
+
+    /// A document of the collection, as it was at `snapshot` if given
+    pub(super) fn read_document(&self, collection: &CollectionName, id: &DocumentId, snapshot: Option<u64>) -> DocumentResult<Option<Document>> {
+        match snapshot {
+            Some(snapshot) => self.storage.get_document_as_of(collection, id, snapshot),
+            None => self.storage.get_document(collection, id),
+        }
+    }
+
+    /// Read a document for a collection scan, as it was at `snapshot` if given
+    ///
+    /// Current documents are read with [`DocumentStorage::get_document_for_scan`], so a scan
+    /// does not push the hot documents out of the database cache.
+    pub(super) fn scan_document(&self, collection: &CollectionName, id: &DocumentId, snapshot: Option<u64>) -> DocumentResult<Option<Document>> {
+        match snapshot {
+            Some(snapshot) => self.storage.get_document_as_of(collection, id, snapshot),
+            None => self.storage.get_document_for_scan(collection, id),
+        }
+    }
+
     /// Update a document with JSON string
     pub fn update_json(&self, collection: &str, id: &DocumentId, json: &str) -> DocumentResult<()> {
-        let collection_name = CollectionName::new(collection);
-        let content: Value = serde_json::from_str(json)?;
-        let document = Document::with_id(id.clone(), content);
-        self.storage.update_document(&collection_name, document)
+        self.update_value_with(collection, id, serde_json::from_str(json)?, Validation::Enforce)
     }
 
     /// Update a document with JSON value
     pub fn update_value(&self, collection: &str, id: &DocumentId, value: Value) -> DocumentResult<()> {
+        self.update_value_with(collection, id, value, Validation::Enforce)
+    }
+
+    /// Update a document with JSON value, checking it against the collection's schema
+    /// unless `validation` is [`Validation::Skip`]
+    pub fn update_value_with(&self, collection: &str, id: &DocumentId, value: Value, validation: Validation) -> DocumentResult<()> {
+        let collection_name = CollectionName::new(collection);
+        self.validate(&collection_name, &value, validation)?;
+        let document = Document::with_id(id.clone(), value);
+        self.advisor.lock().unwrap().record_write(&self.advisor_collection(collection));
+        self.storage.update_document(&collection_name, document)?;
+        self.record_mutation(collection, MutationKind::Update, 1);
+        Ok(())
+    }
+
+    /// Update a document with JSON string if it is still at `expected_version`, returning its
+    /// new version
+    ///
+    /// Fails with [`DocumentError::VersionConflict`], carrying the current version, if the
+    /// document was written since that version was read, and with
+    /// [`DocumentError::DocumentNotFound`] if it no longer exists.
+    pub fn update_json_if_version(&self, collection: &str, id: &DocumentId, json: &str, expected_version: u64) -> DocumentResult<u64> {
+        self.update_value_if_version(collection, id, serde_json::from_str(json)?, expected_version)
+    }
+
+    /// Update a document with JSON value if it is still at `expected_version`, returning its
+    /// new version
+    pub fn update_value_if_version(&self, collection: &str, id: &DocumentId, value: Value, expected_version: u64) -> DocumentResult<u64> {
         let collection_name = CollectionName::new(collection);
+        self.validate(&collection_name, &value, Validation::Enforce)?;
         let document = Document::with_id(id.clone(), value);
-        self.storage.update_document(&collection_name, document)
+        self.advisor.lock().unwrap().record_write(&self.advisor_collection(collection));
+        let version = self.storage.update_document_if_version(&collection_name, document, expected_version)?;
+        self.record_mutation(collection, MutationKind::Update, 1);
+        Ok(version)
+    }
+
+    /// Write a JSON value under a caller-chosen ID, replacing the document if there is one
+    ///
+    /// Returns whether the document was created.
+    pub fn put_value(&self, collection: &str, id: &DocumentId, value: Value) -> DocumentResult<bool> {
+        let collection_name = CollectionName::new(collection);
+        self.validate(&collection_name, &value, Validation::Enforce)?;
+        let document = Document::with_id(id.clone(), value);
+        self.advisor.lock().unwrap().record_write(&self.advisor_collection(collection));
+        if self.storage.document_exists(&collection_name, id)? {
+            self.storage.update_document(&collection_name, document)?;
+            self.record_mutation(collection, MutationKind::Update, 1);
+            Ok(false)
+        } else {
+            self.storage.create_document(&collection_name, document)?;
+            self.record_mutation(collection, MutationKind::Insert, 1);
+            Ok(true)
+        }
     }
 
     /// Delete a document
     pub fn delete(&self, collection: &str, id: &DocumentId) -> DocumentResult<bool> {
         let collection_name = CollectionName::new(collection);
-        self.storage.delete_document(&collection_name, id)
+        self.advisor.lock().unwrap().record_write(&self.advisor_collection(collection));
+        let deleted = self.storage.delete_document(&collection_name, id)?;
+        if deleted {
+            self.record_mutation(collection, MutationKind::Delete, 1);
+        }
+        Ok(deleted)
+    }
+
+    /// Delete a document if it is still at `expected_version`
+    ///
+    /// Returns whether the document existed; fails with [`DocumentError::VersionConflict`] if
+    /// it was written since that version was read.
+    pub fn delete_if_version(&self, collection: &str, id: &DocumentId, expected_version: u64) -> DocumentResult<bool> {
+        let collection_name = CollectionName::new(collection);
+        self.advisor.lock().unwrap().record_write(&self.advisor_collection(collection));
+        let deleted = self.storage.delete_document_if_version(&collection_name, id, expected_version)?;
+        if deleted {
+            self.record_mutation(collection, MutationKind::Delete, 1);
+        }
+        Ok(deleted)
     }
 
     /// Check if a document exists
@@ -99,6 +447,92 @@ impl CollectionManager {
         self.storage.list_documents(&collection_name)
     }
 
+    /// List the documents of a collection in the order and window given by `options`
+    ///
+    /// With a limit only the first `offset + limit` documents are kept while scanning, so
+    /// a small page of a large collection is never fully sorted. When a covering index
+    /// holds the sort field, documents are ranked from the index and only the returned
+    /// ones are read.
+    pub fn list_documents(&self, collection: &str, options: &ScanOptions) -> DocumentResult<Vec<(DocumentId, Value)>> {
+        self.scan_documents(collection, options, None)
+    }
+
+    /// List the documents of a collection as it was at `timestamp`, in nanoseconds since
+    /// the Unix epoch, in the order and window given by `options`
+    ///
+    /// Covering indexes only hold current values, so documents are always read.
+    pub fn list_documents_as_of(&self, collection: &str, options: &ScanOptions, timestamp: u64) -> DocumentResult<Vec<(DocumentId, Value)>> {
+        let snapshot = self.storage.read_snapshot(timestamp)?;
+        self.scan_documents(collection, options, Some(snapshot))
+    }
+
+    pub(super) fn scan_documents(&self, collection: &str, options: &ScanOptions, snapshot: Option<u64>) -> DocumentResult<Vec<(DocumentId, Value)>> {
+        let collection_name = CollectionName::new(collection);
+        let keep = options.limit.map(|limit| options.offset.saturating_add(limit));
+        if keep == Some(0) {
+            return Ok(Vec::new());
+        }
+
+        let mut ranking = Ranking::new(options.descending, keep);
+        match &options.sort_field {
+            None => {
+                for id in self.document_ids(&collection_name, snapshot)? {
+                    ranking.push(ProjectedValue::Missing, id, None);
+                }
+            }
+            Some(sort_field) => {
+                let projection = Projection::new(&[sort_field])?;
+                let covering = match snapshot {
+                    Some(_) => None,
+                    None => self.storage.covering_indexes(&collection_name)?.into_iter().find(|indexed| projection.is_covered_by(indexed)),
+                };
+
+                match covering.and_then(|paths| self.storage.read_covering_index(&collection_name, &paths).transpose()) {
+                    Some(index) => {
+                        let index = index?;
+                        let position = projection.paths().next().and_then(|path| index.paths.iter().position(|indexed| indexed == path));
+                        for (id, mut values) in index.entries {
+                            let key = position.map_or(ProjectedValue::Missing, |i| values.swap_remove(i));
+                            ranking.push(key, id, None);
+                        }
+                    }
+                    None => {
+                        for id in self.document_ids(&collection_name, snapshot)? {
+                            if let Some(document) = self.scan_document(&collection_name, &id, snapshot)? {
+                                let key = projection.project(&document.content).pop().unwrap_or(ProjectedValue::Missing);
+                                ranking.push(key, id, Some(document.content));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let rows_scanned = ranking.scanned;
+        let mut documents = Vec::new();
+        for row in ranking.into_sorted().into_iter().skip(options.offset) {
+            let content = match row.content {
+                Some(content) => Some(content),
+                // Documents deleted since they were ranked are skipped
+                None => self.read_document(&collection_name, &row.id, snapshot)?.map(|document| document.content),
+            };
+            if let Some(content) = content {
+                documents.push((row.id, content));
+            }
+        }
+
+        if let Some(sort_field) = &options.sort_field {
+            self.record_field_access(FieldAccess {
+                collection: collection.to_string(),
+                field: sort_field.clone(),
+                kind: FieldAccessKind::Sort,
+                rows_scanned,
+                rows_returned: documents.len() as u64,
+            });
+        }
+        Ok(documents)
+    }
+
     /// Get all documents in a collection as JSON values
     pub fn get_all_values(&self, collection: &str) -> DocumentResult<Vec<(DocumentId, Value)>> {
         let collection_name = CollectionName::new(collection);
@@ -106,7 +540,7 @@ impl CollectionManager {
         let mut documents = Vec::new();
 
         for id in doc_ids {
-            if let Some(document) = self.storage.get_document(&collection_name, &id)? {
+            if let Some(document) = self.storage.get_document_for_scan(&collection_name, &id)? {
                 documents.push((id, document.content));
             }
         }
@@ -120,6 +554,13 @@ impl CollectionManager {
         self.storage.count_documents(&collection_name)
     }
 
+    /// Count the documents a collection held at `timestamp`, in nanoseconds since the
+    /// Unix epoch
+    pub fn count_as_of(&self, collection: &str, timestamp: u64) -> DocumentResult<usize> {
+        let snapshot = self.storage.read_snapshot(timestamp)?;
+        Ok(self.storage.list_documents_as_of(&CollectionName::new(collection), snapshot)?.len())
+    }
+
     /// Create a collection
     pub fn create_collection(&self, collection: &str) -> DocumentResult<()> {
         let collection_name = CollectionName::new(collection);
@@ -129,7 +570,110 @@ impl CollectionManager {
     /// Delete a collection and all its documents
     pub fn delete_collection(&self, collection: &str) -> DocumentResult<bool> {
         let collection_name = CollectionName::new(collection);
-        self.storage.delete_collection(&collection_name)
+        let deleted = self.storage.delete_collection(&collection_name)?;
+        self.schemas.write().unwrap().remove(self.advisor_collection(collection).as_ref());
+        Ok(deleted)
+    }
+
+    /// Rename a collection with its documents, history, schema, codec and covering indexes
+    ///
+    /// Fails with [`DocumentError::CollectionAlreadyExists`] if `to` exists. Readers find
+    /// the collection under at least one of the two names while it is renamed.
+    pub fn rename_collection(&self, from: &str, to: &str) -> DocumentResult<()> {
+        self.storage.rename_collection(&CollectionName::new(from), &CollectionName::new(to))?;
+        let mut schemas = self.schemas.write().unwrap();
+        schemas.remove(self.advisor_collection(from).as_ref());
+        schemas.remove(self.advisor_collection(to).as_ref());
+        Ok(())
+    }
+
+    /// Copy a collection's documents under the same IDs into a new collection, see
+    /// [`copy_collection_with_progress`](Self::copy_collection_with_progress)
+    pub fn copy_collection(&self, from: &str, to: &str, options: &CopyOptions) -> DocumentResult<CopyReport> {
+        self.copy_collection_with_progress(from, to, options, |_| {})
+    }
+
+    /// Copy a collection's documents under the same IDs into a new collection, calling
+    /// `progress` with the report so far after each batch is written
+    ///
+    /// The source is read from a snapshot pinned when the copy starts, so writes made to
+    /// it meanwhile neither show up in the copy nor tear it; the report names the snapshot
+    /// so they can be replayed. Documents are written in batches, each a commit of its
+    /// own, without checking them against the schema again. Fails with
+    /// [`DocumentError::CollectionAlreadyExists`] if `to` exists, and with
+    /// [`DocumentError::SnapshotReleased`] if the copy outlasts the
+    /// [snapshot maximum age](Self::with_snapshot_max_age).
+    pub fn copy_collection_with_progress(&self, from: &str, to: &str, options: &CopyOptions, mut progress: impl FnMut(&CopyReport)) -> DocumentResult<CopyReport> {
+        let source = CollectionName::new(from);
+        let destination = CollectionName::new(to);
+        if !self.storage.collection_exists(&source)? {
+            return Err(DocumentError::CollectionNotFound(source));
+        }
+        if self.storage.collection_exists(&destination)? {
+            return Err(DocumentError::CollectionAlreadyExists(destination));
+        }
+
+        let pin = self.storage.pin_snapshot(self.snapshot_max_age)?;
+        let ids = self.storage.list_documents_as_of(&source, pin.snapshot())?;
+        let mut report = CopyReport {
+            snapshot: pin.snapshot(),
+            documents_total: ids.len() as u64,
+            ..CopyReport::default()
+        };
+
+        // The destination is set up before documents arrive so they are stored under the codec
+        self.storage.create_collection(&destination)?;
+        self.storage.set_compression(&destination, self.storage.get_compression(&source)?)?;
+        if let Some(schema) = self.storage.get_schema(&source)? {
+            self.storage.set_schema(&destination, Some(&schema))?;
+        }
+        self.schemas.write().unwrap().remove(self.advisor_collection(to).as_ref());
+        let indexes = match options.indexes {
+            IndexCopy::Skip => Vec::new(),
+            IndexCopy::Copy | IndexCopy::Rebuild => self.storage.covering_indexes(&source)?,
+        };
+        if options.indexes == IndexCopy::Copy {
+            for paths in &indexes {
+                self.storage.create_covering_index(&destination, paths)?;
+            }
+        }
+
+        for batch in ids.chunks(COPY_BATCH_SIZE) {
+            if !pin.is_held() {
+                return Err(DocumentError::SnapshotReleased { snapshot: pin.snapshot() });
+            }
+            let mut writes = Vec::new();
+            for id in batch {
+                let Some(document) = self.storage.get_document_as_of(&source, id, pin.snapshot())? else {
+                    continue;
+                };
+                if let Some((field, value)) = &options.filter
+                    && document.content.get(field) != Some(value)
+                {
+                    report.documents_skipped += 1;
+                    continue;
+                }
+                writes.push(DocumentWrite {
+                    collection: destination.clone(),
+                    id: id.clone(),
+                    content: Some(document.content),
+                });
+            }
+            let copied = writes.len() as u64;
+            self.storage.commit_writes(writes)?;
+            self.record_mutation(to, MutationKind::Insert, copied);
+            report.documents_copied += copied;
+            progress(&report);
+        }
+
+        if options.indexes == IndexCopy::Rebuild {
+            for paths in &indexes {
+                self.storage.create_covering_index(&destination, paths)?;
+            }
+        }
+        self.advisor.lock().unwrap().record_write(&self.advisor_collection(to));
+        report.indexes = indexes;
+        Ok(report)
     }
 
     /// List all collections
@@ -144,14 +688,144 @@ impl CollectionManager {
         self.storage.collection_exists(&collection_name)
     }
 
+    /// Logical and on-disk size of a collection
+    pub fn space_usage(&self, collection: &str) -> DocumentResult<CollectionSpaceUsage> {
+        self.storage.space_usage(&CollectionName::new(collection))
+    }
+
+    /// Reclaim the space of deleted and overwritten documents, see [`DocumentStorage::compact`]
+    pub fn compact(&self, strategy: &DeadSpaceStrategy) -> DocumentResult<DocumentCompaction> {
+        self.storage.compact(strategy)
+    }
+
+    /// Compress the documents written to a collection from now on with `codec`
+    ///
+    /// Documents already stored keep the codec they were written with until
+    /// [`rewrite_compression`](Self::rewrite_compression) stores them again; reads
+    /// handle documents of any codec.
+    pub fn set_compression(&self, collection: &str, codec: CompressionCodec) -> DocumentResult<()> {
+        self.storage.set_compression(&CollectionName::new(collection), codec)
+    }
+
+    /// Codec a collection's new documents are compressed with
+    pub fn get_compression(&self, collection: &str) -> DocumentResult<CompressionCodec> {
+        self.storage.get_compression(&CollectionName::new(collection))
+    }
+
+    /// Store every document of a collection again under its current codec
+    ///
+    /// Writers to the collection are only held back while a batch of documents is
+    /// rewritten, not for the whole rewrite.
+    pub fn rewrite_compression(&self, collection: &str) -> DocumentResult<RecompressionReport> {
+        rewrite_in_batches(self.storage.as_ref(), &CollectionName::new(collection))
+    }
+
+    /// [`rewrite_compression`](Self::rewrite_compression) on a background thread
+    pub fn spawn_compression_rewrite(&self, collection: &str) -> std::thread::JoinHandle<DocumentResult<RecompressionReport>> {
+        let storage = self.storage.clone();
+        let collection = CollectionName::new(collection);
+        std::thread::spawn(move || rewrite_in_batches(storage.as_ref(), &collection))
+    }
+
+    /// Require documents written to a collection to match a JSON Schema
+    ///
+    /// See the [`schema`](super::schema) module for the supported keywords. Existing
+    /// documents are left alone; with `validate_existing` they are checked and the ones
+    /// that do not match are reported.
+    pub fn set_schema(&self, collection: &str, schema_json: &str, validate_existing: bool) -> DocumentResult<Option<SchemaReport>> {
+        let schema: Value = serde_json::from_str(schema_json)?;
+        let compiled = Arc::new(JsonSchema::compile(&schema)?);
+        self.storage.set_schema(&CollectionName::new(collection), Some(&schema))?;
+        self.schemas.write().unwrap().insert(self.advisor_collection(collection).into_owned(), Some(compiled.clone()));
+
+        if !validate_existing {
+            return Ok(None);
+        }
+        Ok(Some(self.scan(collection, &compiled)?))
+    }
+
+    /// The collection's JSON Schema, if one is set
+    pub fn get_schema(&self, collection: &str) -> DocumentResult<Option<Value>> {
+        self.storage.get_schema(&CollectionName::new(collection))
+    }
+
+    /// Stop enforcing a schema on a collection, returning whether it had one
+    pub fn remove_schema(&self, collection: &str) -> DocumentResult<bool> {
+        let collection_name = CollectionName::new(collection);
+        let existed = self.storage.get_schema(&collection_name)?.is_some();
+        self.storage.set_schema(&collection_name, None)?;
+        self.schemas.write().unwrap().insert(self.advisor_collection(collection).into_owned(), None);
+        Ok(existed)
+    }
+
+    /// Check every document in a collection against its schema, or `None` if it has none
+    pub fn check_schema(&self, collection: &str) -> DocumentResult<Option<SchemaReport>> {
+        match self.schema(collection)? {
+            Some(schema) => Ok(Some(self.scan(collection, &schema)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn scan(&self, collection: &str, schema: &JsonSchema) -> DocumentResult<SchemaReport> {
+        let mut report = SchemaReport::default();
+        for (id, content) in self.get_all_values(collection)? {
+            report.documents_checked += 1;
+            let violations = schema.validate(&content);
+            if !violations.is_empty() {
+                report.invalid.push(DocumentViolations { id, violations });
+            }
+        }
+        Ok(report)
+    }
+
+    /// The collection's compiled schema, loaded from storage on first use
+    fn schema(&self, collection: &str) -> DocumentResult<Option<Arc<JsonSchema>>> {
+        let key = self.advisor_collection(collection);
+        if let Some(cached) = self.schemas.read().unwrap().get(key.as_ref()) {
+            return Ok(cached.clone());
+        }
+
+        let schema = match self.storage.get_schema(&CollectionName::new(collection))? {
+            Some(schema) => Some(Arc::new(JsonSchema::compile(&schema)?)),
+            None => None,
+        };
+        self.schemas.write().unwrap().insert(key.into_owned(), schema.clone());
+        Ok(schema)
+    }
+
+    fn validate(&self, collection: &CollectionName, value: &Value, validation: Validation) -> DocumentResult<()> {
+        if validation == Validation::Skip {
+            return Ok(());
+        }
+        match self.schema(collection.as_str())? {
+            Some(schema) => schema.check_document(collection, value),
+            None => Ok(()),
+        }
+    }
+
     /// Find documents by a simple field match (basic query functionality)
     pub fn find_by_field(&self, collection: &str, field: &str, value: &Value) -> DocumentResult<Vec<(DocumentId, Value)>> {
+        self.match_field(collection, field, value, None)
+    }
+
+    /// Find documents by a simple field match like [`find_by_field`](Self::find_by_field),
+    /// in the collection as it was at `timestamp`, in nanoseconds since the Unix epoch
+    ///
+    /// Every document is read from the same snapshot, so the result reflects a single
+    /// point in time even while writes continue.
+    pub fn find_by_field_as_of(&self, collection: &str, field: &str, value: &Value, timestamp: u64) -> DocumentResult<Vec<(DocumentId, Value)>> {
+        let snapshot = self.storage.read_snapshot(timestamp)?;
+        self.match_field(collection, field, value, Some(snapshot))
+    }
+
+    pub(super) fn match_field(&self, collection: &str, field: &str, value: &Value, snapshot: Option<u64>) -> DocumentResult<Vec<(DocumentId, Value)>> {
         let collection_name = CollectionName::new(collection);
-        let doc_ids = self.storage.list_documents(&collection_name)?;
+        let doc_ids = self.document_ids(&collection_name, snapshot)?;
+        let rows_scanned = doc_ids.len() as u64;
         let mut matching_docs = Vec::new();
 
         for id in doc_ids {
-            if let Some(document) = self.storage.get_document(&collection_name, &id)?
+            if let Some(document) = self.scan_document(&collection_name, &id, snapshot)?
                 && let Some(field_value) = document.content.get(field)
                 && field_value == value
             {
@@ -159,22 +833,434 @@ impl CollectionManager {
             }
         }
 
+        self.record_field_access(FieldAccess {
+            collection: collection.to_string(),
+            field: field.to_string(),
+            kind: FieldAccessKind::Equality,
+            rows_scanned,
+            rows_returned: matching_docs.len() as u64,
+        });
+        Ok(matching_docs)
+    }
+
+    /// Find documents by a simple field match like [`find_by_field`](Self::find_by_field),
+    /// returning only the projected `paths` of each
+    ///
+    /// When a covering index (see [`create_covering_index`](Self::create_covering_index))
+    /// holds `field` and every projected path, the query is answered from the index and no
+    /// document body is read. Projection only shapes what is returned, so documents that do
+    /// not match the collection's schema are returned like any others.
+    pub fn find_by_field_projected(&self, collection: &str, field: &str, value: &Value, paths: &[&str]) -> DocumentResult<Vec<ProjectedDocument>> {
+        let projection = Projection::new(paths)?;
+        let collection_name = CollectionName::new(collection);
+        let projected_paths: Vec<String> = projection.paths().map(str::to_string).collect();
+
+        // Index paths are nested paths while `field` names a top-level field, so only
+        // single-segment fields can be matched against an index
+        let covering = if field.contains('.') {
+            None
+        } else {
+            self.storage
+                .covering_indexes(&collection_name)?
+                .into_iter()
+                .find(|indexed| indexed.iter().any(|path| path == field) && projection.is_covered_by(indexed))
+        };
+
+        let (rows_scanned, matching_docs) = match covering.and_then(|paths| self.storage.read_covering_index(&collection_name, &paths).transpose()) {
+            Some(index) => {
+                let index = index?;
+                let position = |path: &str| index.paths.iter().position(|indexed| indexed == path);
+                let field_position = position(field);
+                let positions: Vec<Option<usize>> = projection.paths().map(position).collect();
+
+                let matching_docs: Vec<ProjectedDocument> = index
+                    .entries
+                    .iter()
+                    .filter(|(_, values)| matches!(field_position.map(|i| &values[i]), Some(ProjectedValue::Present(field_value)) if field_value == value))
+                    .map(|(id, values)| ProjectedDocument {
+                        id: id.clone(),
+                        fields: projected_paths
+                            .iter()
+                            .zip(&positions)
+                            .map(|(path, i)| (path.clone(), i.map_or(ProjectedValue::Missing, |i| values[i].clone())))
+                            .collect(),
+                    })
+                    .collect();
+                (index.entries.len() as u64, matching_docs)
+            }
+            None => {
+                let doc_ids = self.storage.list_documents(&collection_name)?;
+                let rows_scanned = doc_ids.len() as u64;
+                let mut matching_docs = Vec::new();
+
+                for id in doc_ids {
+                    if let Some(document) = self.storage.get_document_for_scan(&collection_name, &id)?
+                        && document.content.get(field) == Some(value)
+                    {
+                        let fields = projected_paths.iter().cloned().zip(projection.project(&document.content)).collect();
+                        matching_docs.push(ProjectedDocument { id, fields });
+                    }
+                }
+                (rows_scanned, matching_docs)
+            }
+        };
+
+        self.record_field_access(FieldAccess {
+            collection: collection.to_string(),
+            field: field.to_string(),
+            kind: FieldAccessKind::Equality,
+            rows_scanned,
+            rows_returned: matching_docs.len() as u64,
+        });
         Ok(matching_docs)
     }
 
+    /// Create a covering index over `paths`, returning whether it was created
+    ///
+    /// Projected finds whose match field and projected paths are all among `paths` are then
+    /// served from the index without reading documents.
+    pub fn create_covering_index(&self, collection: &str, paths: &[&str]) -> DocumentResult<bool> {
+        let paths: Vec<String> = Projection::new(paths)?.paths().map(str::to_string).collect();
+        self.storage.create_covering_index(&CollectionName::new(collection), &paths)
+    }
+
+    /// Drop the covering index over `paths`, returning whether it existed
+    pub fn drop_covering_index(&self, collection: &str, paths: &[&str]) -> DocumentResult<bool> {
+        let paths: Vec<String> = Projection::new(paths)?.paths().map(str::to_string).collect();
+        self.storage.drop_covering_index(&CollectionName::new(collection), &paths)
+    }
+
+    /// Report a field access made by a query layer so the index advisor can weigh it
+    pub fn record_field_access(&self, mut access: FieldAccess) {
+        access.collection = self.advisor_collection(&access.collection).into_owned();
+        self.advisor.lock().unwrap().record_access(access);
+    }
+
+    /// Fields worth indexing given the accesses and writes seen in the advisor's window,
+    /// best first. Fields indexed with [`build_field_index`](Self::build_field_index) are
+    /// left out. Recommendations cover every namespace; collections outside the default
+    /// namespace are reported as `namespace.collection`.
+    pub fn index_recommendations(&self) -> Vec<IndexRecommendation> {
+        self.advisor.lock().unwrap().recommendations()
+    }
+
+    /// Backfill a B+ tree index over `field` for every document already in the collection.
+    ///
+    /// Keys are `(serialized field value, document ID)` so documents sharing a value stay
+    /// distinct; values are document IDs. Documents without the field are skipped. The tree
+    /// is bulk loaded rather than built by repeated inserts, with `fill_factor` controlling
+    /// how much room each node leaves for later inserts.
+    pub fn build_field_index(&self, collection: &str, field: &str, order: usize, fill_factor: f64) -> DocumentResult<BPlusTree<CompositeKey, String>> {
+        let mut entries = Vec::new();
+        for (id, content) in self.get_all_values(collection)? {
+            if let Some(field_value) = content.get(field) {
+                let id = id.to_string();
+                let key = CompositeKey::new(vec![serde_json::to_vec(field_value)?, id.clone().into_bytes()]);
+                entries.push((key, id));
+            }
+        }
+
+        let index = BPlusTree::from_entries(order, fill_factor, entries)?;
+        self.advisor.lock().unwrap().register_index(&self.advisor_collection(collection), field);
+        Ok(index)
+    }
+
+    /// Backfill a B+ tree over the timestamp at `field` for
+    /// [`aggregate_indexed`](Self::aggregate_indexed)
+    ///
+    /// Keys are `(timestamp, document ID)` with timestamps encoded so keys sort in time
+    /// order; values are document IDs. Timestamps are read like an aggregation's window
+    /// field reads them, and documents without one are skipped.
+    pub fn build_time_index(&self, collection: &str, field: &str, order: usize, fill_factor: f64) -> DocumentResult<BPlusTree<CompositeKey, String>> {
+        let projection = Projection::new(&[field])?;
+        let mut entries = Vec::new();
+        for (id, content) in self.get_all_values(collection)? {
+            if let Some(timestamp) = projection.project(&content).first().and_then(aggregation::timestamp) {
+                let id = id.to_string();
+                entries.push((CompositeKey::new(vec![aggregation::timestamp_key(timestamp), id.clone().into_bytes()]), id));
+            }
+        }
+
+        let index = BPlusTree::from_entries(order, fill_factor, entries)?;
+        self.advisor.lock().unwrap().register_index(&self.advisor_collection(collection), field);
+        Ok(index)
+    }
+
+    /// Group the documents of a collection and aggregate each group as `spec` describes
+    ///
+    /// Documents are read and folded into their groups one at a time; see the
+    /// [`aggregation`](super::aggregation) module for how groups, windows and skipped
+    /// values work.
+    pub fn aggregate(&self, collection: &str, spec: &AggregationSpec) -> DocumentResult<AggregationResult> {
+        self.aggregate_at(collection, spec, None)
+    }
+
+    /// Aggregate the collection's documents, as they were at `snapshot` if given
+    pub(super) fn aggregate_at(&self, collection: &str, spec: &AggregationSpec, snapshot: Option<u64>) -> DocumentResult<AggregationResult> {
+        let mut aggregator = Aggregator::new(spec)?;
+        let collection_name = CollectionName::new(collection);
+        for id in self.document_ids(&collection_name, snapshot)? {
+            // Documents deleted since the IDs were listed are skipped
+            if let Some(document) = self.scan_document(&collection_name, &id, snapshot)? {
+                aggregator.push(&document.content);
+            }
+        }
+        Ok(self.finish_aggregation(collection, spec, aggregator))
+    }
+
+    /// Aggregate like [`aggregate`](Self::aggregate), reading only the documents whose
+    /// timestamp `index` places in the window's `from`/`to` range
+    ///
+    /// `index` comes from [`build_time_index`](Self::build_time_index) over the window
+    /// field. Every document read is checked against the range again, so documents
+    /// updated since the index was built are placed by their current timestamp, but
+    /// documents inserted since then are not seen. Without a range the whole index is read.
+    pub fn aggregate_indexed(&self, collection: &str, spec: &AggregationSpec, index: &BPlusTree<CompositeKey, String>) -> DocumentResult<AggregationResult> {
+        let mut aggregator = Aggregator::new(spec)?;
+        let Some((from, to)) = aggregator.range() else {
+            return Err(DocumentError::InvalidAggregation("an indexed aggregation needs a time window".to_string()));
+        };
+        let start = CompositeKey::new(vec![aggregation::timestamp_key(from.unwrap_or(i64::MIN))]);
+        let end = CompositeKey::new(vec![aggregation::timestamp_key(to.map_or(i64::MAX, |to| to - 1)), vec![u8::MAX]]);
+
+        let collection_name = CollectionName::new(collection);
+        for (_, id) in index.range_iter(&start, &end) {
+            let id = DocumentId::from_string(&id).map_err(|_| DocumentError::InvalidDocumentId(id))?;
+            if let Some(document) = self.storage.get_document(&collection_name, &id)? {
+                aggregator.push(&document.content);
+            }
+        }
+        Ok(self.finish_aggregation(collection, spec, aggregator))
+    }
+
+    fn finish_aggregation(&self, collection: &str, spec: &AggregationSpec, aggregator: Aggregator) -> AggregationResult {
+        let result = aggregator.finish();
+        if let Some(window) = spec.window.as_ref().filter(|window| window.from.is_some() || window.to.is_some()) {
+            self.record_field_access(FieldAccess {
+                collection: collection.to_string(),
+                field: window.field.clone(),
+                kind: FieldAccessKind::Range,
+                rows_scanned: result.documents_scanned,
+                rows_returned: result.documents_aggregated,
+            });
+        }
+        if result.warnings.total() > 0 {
+            warn!(collection, warnings = ?result.warnings, "Skipped values while aggregating");
+        }
+        result
+    }
+
+    /// Flush buffered writes to durable storage, along with the index advisor's window
+    /// if it has a persistence path
+    pub fn flush(&self) -> DocumentResult<()> {
+        let advisor = self.advisor.lock().unwrap();
+        if advisor.config().persistence_path.is_some() {
+            advisor.persist()?;
+        }
+        drop(advisor);
+        self.storage.flush()
+    }
+
     /// Get the underlying storage interface
     pub fn storage(&self) -> &Arc<dyn DocumentStorage> {
         &self.storage
     }
 }
 
+/// Rewrite a collection's documents under its current codec in batches; documents
+/// created meanwhile are written with that codec already
+fn rewrite_in_batches(storage: &dyn DocumentStorage, collection: &CollectionName) -> DocumentResult<RecompressionReport> {
+    let mut report = RecompressionReport::default();
+    for batch in storage.list_documents(collection)?.chunks(REWRITE_BATCH_SIZE) {
+        report.merge(storage.rewrite_documents(collection, batch)?);
+    }
+    Ok(report)
+}
+
+/// A document ranked by a scan, ordered by sort key then ID in the scan's direction
+struct RankedRow {
+    key: ProjectedValue,
+    id: DocumentId,
+    /// The document, when the scan had to read it to find the key
+    content: Option<Value>,
+    descending: bool,
+}
+
+impl Ord for RankedRow {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let order = match (&self.key, &other.key) {
+            (ProjectedValue::Missing, ProjectedValue::Missing) => Ordering::Equal,
+            (ProjectedValue::Missing, ProjectedValue::Present(_)) => Ordering::Less,
+            (ProjectedValue::Present(_), ProjectedValue::Missing) => Ordering::Greater,
+            (ProjectedValue::Present(a), ProjectedValue::Present(b)) => compare_values(a, b),
+        }
+        .then_with(|| self.id.cmp(&other.id));
+        if self.descending { order.reverse() } else { order }
+    }
+}
+
+impl PartialOrd for RankedRow {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for RankedRow {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for RankedRow {}
+
+/// The first `keep` rows pushed, held in a max-heap so each push past the bound evicts the
+/// current last row; without a bound every row is kept
+struct Ranking {
+    rows: BinaryHeap<RankedRow>,
+    descending: bool,
+    keep: Option<usize>,
+    scanned: u64,
+}
+
+impl Ranking {
+    fn new(descending: bool, keep: Option<usize>) -> Self {
+        Self {
+            rows: BinaryHeap::with_capacity(keep.map_or(0, |keep| keep.saturating_add(1).min(4096))),
+            descending,
+            keep,
+            scanned: 0,
+        }
+    }
+
+    fn push(&mut self, key: ProjectedValue, id: DocumentId, content: Option<Value>) {
+        self.scanned += 1;
+        let row = RankedRow {
+            key,
+            id,
+            content,
+            descending: self.descending,
+        };
+        match self.keep {
+            Some(keep) if self.rows.len() >= keep => {
+                if self.rows.peek().is_some_and(|last| row < *last) {
+                    self.rows.pop();
+                    self.rows.push(row);
+                }
+            }
+            _ => self.rows.push(row),
+        }
+    }
+
+    fn into_sorted(self) -> Vec<RankedRow> {
+        self.rows.into_sorted_vec()
+    }
+}
+
+/// Total order over JSON values: null, booleans, numbers, strings, arrays, then objects
+///
+/// Arrays compare element by element; objects compare entry by entry in key order.
+pub(super) fn compare_values(a: &Value, b: &Value) -> Ordering {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Null => 0,
+            Value::Bool(_) => 1,
+            Value::Number(_) => 2,
+            Value::String(_) => 3,
+            Value::Array(_) => 4,
+            Value::Object(_) => 5,
+        }
+    }
+
+    match (a, b) {
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::Number(a), Value::Number(b)) => match (a.as_i64(), b.as_i64(), a.as_u64(), b.as_u64()) {
+            (Some(a), Some(b), _, _) => a.cmp(&b),
+            (_, _, Some(a), Some(b)) => a.cmp(&b),
+            _ => a.as_f64().unwrap_or(f64::NAN).total_cmp(&b.as_f64().unwrap_or(f64::NAN)),
+        },
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Array(a), Value::Array(b)) => a.iter().zip(b).map(|(a, b)| compare_values(a, b)).find(|order| order.is_ne()).unwrap_or_else(|| a.len().cmp(&b.len())),
+        (Value::Object(a), Value::Object(b)) => a
+            .iter()
+            .zip(b)
+            .map(|((a_key, a), (b_key, b))| a_key.cmp(b_key).then_with(|| compare_values(a, b)))
+            .find(|order| order.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+impl CollectionManager {
+    /// Handle and collection a statistics table name refers to, undoing the qualification
+    /// [`advisor_collection`](Self::advisor_collection) gives collections outside the default namespace
+    fn statistics_table<'a>(&self, table: &'a str) -> DocumentResult<(Self, &'a str)> {
+        if let Some((namespace, collection)) = table.split_once('.')
+            && namespace != self.namespace()
+            && self.list_namespaces()?.iter().any(|existing| existing == namespace)
+        {
+            return Ok((self.with_namespace(namespace)?, collection));
+        }
+        Ok((self.share(), table))
+    }
+}
+
+/// Tables are the manager's collections, qualified by namespace outside the default one;
+/// sampling lists document IDs and reads only the sampled documents
+impl AnalyzeSource for CollectionManager {
+    fn row_count(&self, table: &str) -> StatisticsResult<u64> {
+        let (manager, collection) = self.statistics_table(table).map_err(|e| StatisticsError::CollectionFailed(e.to_string()))?;
+        manager.count(collection).map(|count| count as u64).map_err(|e| StatisticsError::CollectionFailed(e.to_string()))
+    }
+
+    fn sample_rows(&self, table: &str, limit: usize) -> StatisticsResult<Vec<Value>> {
+        let (manager, collection) = self.statistics_table(table).map_err(|e| StatisticsError::CollectionFailed(e.to_string()))?;
+        let ids = manager.list_document_ids(collection).map_err(|e| StatisticsError::CollectionFailed(e.to_string()))?;
+        let picked = rand::seq::index::sample(&mut rand::thread_rng(), ids.len(), limit.min(ids.len()));
+
+        let mut rows = Vec::with_capacity(picked.len());
+        for index in picked {
+            // Documents deleted since the IDs were listed are skipped
+            if let Some(value) = manager.get_value(collection, &ids[index]).map_err(|e| StatisticsError::CollectionFailed(e.to_string()))? {
+                rows.push(value);
+            }
+        }
+        Ok(rows)
+    }
+}
+
 /// Helper function to create a collection manager with in-memory storage
+///
+/// The manager supports the whole [`CollectionManager`] API, including namespaces, schemas,
+/// indexes, history and transactions, but never touches disk: there is no write-ahead log and
+/// no file IO. Compared with [`create_persistent_collection_manager`] it differs in:
+///
+/// - Durability: documents live as long as the manager, `flush` has nothing to write, and
+///   nothing survives a restart.
+/// - Space: there are no append log segments, so [`CollectionManager::space_usage`] never
+///   reports dead space and [`CollectionManager::compact`] has nothing to reclaim.
+///
+/// Isolation is the same for both, as transactions are implemented above the storage.
 pub fn create_in_memory_collection_manager() -> DocumentResult<CollectionManager> {
-    use super::storage::DocumentStore;
     use crate::state::db_interface::Database;
 
-    let db = Arc::new(Database::new_in_memory()?);
-    let storage = Arc::new(DocumentStore::new(db));
+    in_memory_collection_manager(Database::new_in_memory()?)
+}
+
+/// Helper function to create an in-memory collection manager whose stored data may take at most `budget` bytes
+///
+/// Writes past the budget fail with [`DbError::MemoryBudgetExceeded`](crate::state::db_interface::DbError::MemoryBudgetExceeded).
+/// Writes touching several keys are not atomic in either backend, so a refused write may leave
+/// part of its changes stored. Otherwise the manager behaves as [`create_in_memory_collection_manager`].
+pub fn create_in_memory_collection_manager_with_budget(budget: usize) -> DocumentResult<CollectionManager> {
+    use crate::state::db_interface::Database;
+
+    in_memory_collection_manager(Database::new_in_memory_with_budget(budget)?)
+}
+
+fn in_memory_collection_manager(db: crate::state::db_interface::Database) -> DocumentResult<CollectionManager> {
+    use super::storage::DocumentStore;
+
+    let storage = Arc::new(DocumentStore::open(Arc::new(db))?);
     Ok(CollectionManager::new(storage))
 }
 
@@ -185,19 +1271,97 @@ pub fn create_persistent_collection_manager<P: AsRef<std::path::Path>>(path: P,
 
     let config = config.unwrap_or_default();
     let db = Arc::new(Database::new(path, config)?);
-    let storage = Arc::new(DocumentStore::new(db));
+    let storage = Arc::new(DocumentStore::open(db)?);
     Ok(CollectionManager::new(storage))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::document::DocumentError;
     use serde_json::json;
 
     fn create_test_manager() -> CollectionManager {
         create_in_memory_collection_manager().unwrap()
     }
 
+    #[test]
+    fn test_build_field_index() {
+        use crate::indices::{Index, IndexMaintenance, RangeQuery};
+
+        let manager = create_test_manager();
+        for i in 0..500 {
+            manager.insert_value("users", json!({"name": format!("user_{i}"), "group": i % 5})).unwrap();
+        }
+        manager.insert_value("users", json!({"name": "no_group"})).unwrap();
+
+        let index = manager.build_field_index("users", "group", 16, 0.8).unwrap();
+        assert_eq!(index.len(), 500);
+        assert!(index.verify().unwrap());
+
+        // All documents sharing a value are adjacent in key order
+        let group = serde_json::to_vec(&json!(3)).unwrap();
+        let start = CompositeKey::new(vec![group.clone()]);
+        let end = CompositeKey::new(vec![group, vec![u8::MAX]]);
+        let matches = index.range(&start, &end).unwrap();
+        assert_eq!(matches.len(), 100);
+        for (_, id) in matches {
+            let id = DocumentId::from_string(&id).unwrap();
+            assert_eq!(manager.get_value("users", &id).unwrap().unwrap()["group"], json!(3));
+        }
+    }
+
+    #[test]
+    fn test_index_recommendations_from_find_by_field() {
+        let manager = create_test_manager();
+        for i in 0..20 {
+            manager.insert_value("users", json!({"email": format!("user{i}@example.com"), "group": i % 2})).unwrap();
+        }
+
+        for i in 0..5 {
+            manager.find_by_field("users", "email", &json!(format!("user{i}@example.com"))).unwrap();
+        }
+
+        // Too few lookups to be considered yet
+        assert!(manager.index_recommendations().is_empty());
+
+        for i in 5..20 {
+            manager.find_by_field("users", "email", &json!(format!("user{i}@example.com"))).unwrap();
+        }
+        let recommendations = manager.index_recommendations();
+        assert_eq!(recommendations.len(), 1);
+        assert_eq!(recommendations[0].field, "email");
+        assert_eq!(recommendations[0].equality_lookups, 20);
+
+        // Once the field is indexed it is no longer recommended
+        manager.build_field_index("users", "email", 4, 1.0).unwrap();
+        assert!(manager.index_recommendations().is_empty());
+    }
+
+    #[test]
+    fn test_with_namespace_scopes_collections() {
+        let manager = create_test_manager();
+        let tenant_a = manager.with_namespace("tenant_a").unwrap();
+        let tenant_b = manager.with_namespace("tenant_b").unwrap();
+
+        let id = tenant_a.insert_value("users", json!({"name": "Alice"})).unwrap();
+        tenant_b.insert_value("users", json!({"name": "Bob"})).unwrap();
+
+        assert_eq!(tenant_a.namespace(), "tenant_a");
+        assert!(tenant_a.exists("users", &id).unwrap());
+        assert!(!tenant_b.exists("users", &id).unwrap());
+        assert_eq!(tenant_b.count("users").unwrap(), 1);
+        assert!(manager.list_collections().unwrap().is_empty());
+        assert_eq!(manager.list_namespaces().unwrap(), vec!["default", "tenant_a", "tenant_b"]);
+
+        assert!(manager.drop_namespace("tenant_a", false).is_err());
+        assert!(manager.drop_namespace("tenant_a", true).unwrap());
+        assert!(!tenant_a.exists("users", &id).unwrap());
+        assert_eq!(tenant_b.count("users").unwrap(), 1);
+
+        assert!(manager.with_namespace("bad:name").is_err());
+    }
+
     #[test]
     fn test_insert_and_get_json() {
         let manager = create_test_manager();
@@ -265,6 +1429,66 @@ mod tests {
         assert!(!deleted_again);
     }
 
+    #[test]
+    fn test_versioned_update_and_delete() {
+        let manager = create_test_manager();
+        let id = manager.insert_value("counters", json!({"hits": 1})).unwrap();
+        let (_, version) = manager.get_value_with_version("counters", &id).unwrap().unwrap();
+
+        let updated = manager.update_json_if_version("counters", &id, r#"{"hits": 2}"#, version).unwrap();
+        assert_eq!(updated, version + 1);
+        assert_eq!(manager.get_value_with_version("counters", &id).unwrap(), Some((json!({"hits": 2}), updated)));
+
+        // A writer still holding the old version is turned away with the current one
+        match manager.update_value_if_version("counters", &id, json!({"hits": 5}), version) {
+            Err(DocumentError::VersionConflict { expected, current, .. }) => assert_eq!((expected, current), (version, updated)),
+            other => panic!("expected a version conflict, got {other:?}"),
+        }
+        assert!(matches!(manager.delete_if_version("counters", &id, version), Err(DocumentError::VersionConflict { .. })));
+        assert_eq!(manager.get_value("counters", &id).unwrap(), Some(json!({"hits": 2})));
+
+        assert!(manager.delete_if_version("counters", &id, updated).unwrap());
+        assert!(!manager.delete_if_version("counters", &id, updated).unwrap());
+        assert!(matches!(manager.update_value_if_version("counters", &id, json!({}), updated), Err(DocumentError::DocumentNotFound(_))));
+    }
+
+    #[test]
+    fn test_racing_versioned_updates_have_one_winner() {
+        let manager = Arc::new(create_test_manager());
+        let id = manager.insert_value("counters", json!({"hits": 0})).unwrap();
+        let (_, version) = manager.get_value_with_version("counters", &id).unwrap().unwrap();
+        let barrier = Arc::new(std::sync::Barrier::new(2));
+
+        let updaters: Vec<_> = (1..=2)
+            .map(|hits| {
+                let (manager, id, barrier) = (manager.clone(), id.clone(), barrier.clone());
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    manager.update_value_if_version("counters", &id, json!({ "hits": hits }), version)
+                })
+            })
+            .collect();
+        let results: Vec<_> = updaters.into_iter().map(|updater| updater.join().unwrap()).collect();
+
+        let winners: Vec<_> = results.iter().filter_map(|result| result.as_ref().ok()).collect();
+        assert_eq!(winners, [&(version + 1)]);
+        let conflict = results.iter().find_map(|result| result.as_ref().err()).unwrap();
+        assert!(matches!(conflict, DocumentError::VersionConflict { current, .. } if *current == version + 1));
+        assert_eq!(manager.get_value_with_version("counters", &id).unwrap().unwrap().1, version + 1);
+    }
+
+    #[test]
+    fn test_put_value_creates_then_replaces() {
+        let manager = create_test_manager();
+        let id = DocumentId::new();
+
+        assert!(manager.put_value("users", &id, json!({"name": "Erin"})).unwrap());
+        assert!(!manager.put_value("users", &id, json!({"name": "Erin", "age": 41})).unwrap());
+
+        assert_eq!(manager.get_value("users", &id).unwrap(), Some(json!({"name": "Erin", "age": 41})));
+        assert_eq!(manager.count("users").unwrap(), 1);
+    }
+
     #[test]
     fn test_collection_operations() {
         let manager = create_test_manager();
@@ -287,6 +1511,79 @@ mod tests {
         assert!(!manager.collection_exists("test").unwrap());
     }
 
+    #[test]
+    fn test_rename_collection_carries_everything() {
+        let manager = create_test_manager();
+        let alice = manager.insert_value("users", json!({"name": "Alice", "status": "active"})).unwrap();
+        let before = crate::storage_engine::generate_timestamp();
+        manager.update_value("users", &alice, json!({"name": "Alice", "status": "away"})).unwrap();
+        manager.insert_value("users", json!({"name": "Bob", "status": "active"})).unwrap();
+        manager.set_schema("users", r#"{"type": "object", "required": ["name"]}"#, false).unwrap();
+        manager.set_compression("users", CompressionCodec::Lz4).unwrap();
+        assert!(manager.create_covering_index("users", &["status"]).unwrap());
+        manager.create_collection("orders").unwrap();
+
+        assert!(matches!(manager.rename_collection("users", "orders"), Err(DocumentError::CollectionAlreadyExists(_))));
+        assert!(matches!(manager.rename_collection("missing", "people"), Err(DocumentError::CollectionNotFound(_))));
+
+        manager.rename_collection("users", "people").unwrap();
+        assert!(!manager.collection_exists("users").unwrap());
+        assert_eq!(manager.count("users").unwrap(), 0);
+        assert_eq!(manager.list_collections().unwrap(), ["people", "orders"]);
+        assert_eq!(manager.count("people").unwrap(), 2);
+        assert_eq!(manager.get_value_as_of("people", &alice, before).unwrap(), Some(json!({"name": "Alice", "status": "active"})));
+        assert_eq!(manager.get_compression("people").unwrap(), CompressionCodec::Lz4);
+        assert!(matches!(manager.insert_value("people", json!({"status": "away"})), Err(DocumentError::SchemaViolation { .. })));
+
+        let active = manager.find_by_field_projected("people", "status", &json!("active"), &["status"]).unwrap();
+        assert_eq!(active.len(), 1);
+        assert_eq!(manager.storage().covering_indexes(&CollectionName::new("people")).unwrap(), [["status"]]);
+    }
+
+    #[test]
+    fn test_copy_collection_reads_a_snapshot() {
+        let manager = create_test_manager();
+        for i in 0..300 {
+            let status = if i % 3 == 0 { "away" } else { "active" };
+            manager.insert_value("users", json!({"n": i, "status": status})).unwrap();
+        }
+        manager.set_compression("users", CompressionCodec::Lz4).unwrap();
+        assert!(manager.create_covering_index("users", &["status"]).unwrap());
+
+        let options = CopyOptions {
+            filter: Some(("status".to_string(), json!("active"))),
+            indexes: IndexCopy::Rebuild,
+        };
+        let mut batches = Vec::new();
+        let report = manager
+            .copy_collection_with_progress("users", "active_users", &options, |progress| {
+                batches.push(progress.documents_copied + progress.documents_skipped);
+                // Writes after the snapshot are not copied
+                manager.insert_value("users", json!({"status": "active"})).unwrap();
+            })
+            .unwrap();
+        assert_eq!(batches, [256, 300]);
+        assert_eq!(report.documents_total, 300);
+        assert_eq!(report.documents_copied, 200);
+        assert_eq!(report.documents_skipped, 100);
+        assert_eq!(report.indexes, [["status"]]);
+        assert_eq!(manager.count("active_users").unwrap(), 200);
+        assert_eq!(manager.count_as_of("users", report.snapshot).unwrap(), 300);
+        assert_eq!(manager.get_compression("active_users").unwrap(), CompressionCodec::Lz4);
+        assert_eq!(manager.find_by_field_projected("active_users", "status", &json!("active"), &["status"]).unwrap().len(), 200);
+
+        let (id, content) = manager.find_by_field("active_users", "n", &json!(1)).unwrap().remove(0);
+        assert_eq!(manager.get_value("users", &id).unwrap(), Some(content));
+
+        let everything = CopyOptions::default();
+        let copy = manager.copy_collection("users", "all_users", &everything).unwrap();
+        assert_eq!(copy.documents_copied, 302);
+        assert!(copy.indexes.is_empty());
+        assert!(manager.storage().covering_indexes(&CollectionName::new("all_users")).unwrap().is_empty());
+        assert!(matches!(manager.copy_collection("users", "all_users", &everything), Err(DocumentError::CollectionAlreadyExists(_))));
+        assert!(matches!(manager.copy_collection("missing", "other", &everything), Err(DocumentError::CollectionNotFound(_))));
+    }
+
     #[test]
     fn test_list_and_count_documents() {
         let manager = create_test_manager();
@@ -312,6 +1609,102 @@ mod tests {
         assert_eq!(all_docs.len(), 3);
     }
 
+    #[test]
+    fn test_list_documents_sorted_and_paged() {
+        let manager = create_test_manager();
+        let mut ids = Vec::new();
+        for age in [30, 25, 30, 41, 25] {
+            ids.push(manager.insert_value("users", json!({"age": age})).unwrap());
+        }
+        let no_age = manager.insert_value("users", json!({"name": "Eve"})).unwrap();
+
+        let ages = |options: &ScanOptions| -> Vec<Value> { manager.list_documents("users", options).unwrap().into_iter().map(|(_, doc)| doc["age"].clone()).collect() };
+        let sorted = ScanOptions {
+            sort_field: Some("age".to_string()),
+            ..ScanOptions::default()
+        };
+        assert_eq!(ages(&sorted), [Value::Null, json!(25), json!(25), json!(30), json!(30), json!(41)]);
+        assert_eq!(
+            ages(&ScanOptions {
+                descending: true,
+                limit: Some(2),
+                ..sorted.clone()
+            }),
+            [json!(41), json!(30)]
+        );
+
+        // Pages of the same scan cover every document once, ties ordered by ID
+        let mut paged = Vec::new();
+        for offset in (0..6).step_by(4) {
+            let page = manager
+                .list_documents(
+                    "users",
+                    &ScanOptions {
+                        limit: Some(4),
+                        offset,
+                        ..sorted.clone()
+                    },
+                )
+                .unwrap();
+            paged.extend(page.into_iter().map(|(id, _)| id));
+        }
+        let mut expected = vec![no_age];
+        for pair in [[&ids[1], &ids[4]], [&ids[0], &ids[2]]] {
+            let (low, high) = if pair[0] < pair[1] { (pair[0], pair[1]) } else { (pair[1], pair[0]) };
+            expected.extend([low.clone(), high.clone()]);
+        }
+        expected.push(ids[3].clone());
+        assert_eq!(paged, expected);
+
+        // Without a sort field documents are ordered by ID
+        let mut by_id = manager.list_document_ids("users").unwrap();
+        by_id.sort();
+        let listed: Vec<DocumentId> = manager.list_documents("users", &ScanOptions::default()).unwrap().into_iter().map(|(id, _)| id).collect();
+        assert_eq!(listed, by_id);
+        assert!(
+            manager
+                .list_documents(
+                    "users",
+                    &ScanOptions {
+                        limit: Some(0),
+                        ..ScanOptions::default()
+                    }
+                )
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_sorted_list_ranked_from_covering_index() {
+        use crate::document::DocumentStore;
+        use crate::state::db_interface::{Database, DatabaseInterface};
+
+        let db = Arc::new(Database::new_in_memory().unwrap());
+        let manager = CollectionManager::new(Arc::new(DocumentStore::open(db.clone()).unwrap()));
+        let mut scores = Vec::new();
+        for score in [7, 3, 9, 1] {
+            scores.push(manager.insert_value("games", json!({"score": score})).unwrap());
+        }
+        assert!(manager.create_covering_index("games", &["score"]).unwrap());
+
+        // Corrupt the bodies of the documents outside the page: only a ranking that never reads them succeeds
+        for id in [&scores[1], &scores[3]] {
+            db.put(format!("doc:default:games:{id}").into_bytes(), b"not json".to_vec()).unwrap();
+        }
+        let options = ScanOptions {
+            sort_field: Some("score".to_string()),
+            descending: true,
+            limit: Some(2),
+            offset: 0,
+        };
+        let top = manager.list_documents("games", &options).unwrap();
+        assert_eq!(top, [(scores[2].clone(), json!({"score": 9})), (scores[0].clone(), json!({"score": 7}))]);
+
+        assert!(manager.drop_covering_index("games", &["score"]).unwrap());
+        assert!(manager.list_documents("games", &options).is_err());
+    }
+
     #[test]
     fn test_find_by_field() {
         let manager = create_test_manager();
@@ -334,6 +1727,269 @@ mod tests {
         assert_eq!(alice[0].1["role"], "admin");
     }
 
+    #[test]
+    fn test_reads_as_of_past_timestamp() {
+        let manager = create_test_manager();
+        let alice = manager.insert_value("users", json!({"name": "Alice", "role": "admin"})).unwrap();
+        let bob = manager.insert_value("users", json!({"name": "Bob", "role": "user"})).unwrap();
+        let before = manager.storage().read_snapshot(u64::MAX).unwrap();
+
+        manager.update_value("users", &bob, json!({"name": "Bob", "role": "admin"})).unwrap();
+        manager.delete("users", &alice).unwrap();
+        manager.insert_value("users", json!({"name": "Carol", "role": "admin"})).unwrap();
+
+        let names = |rows: Vec<(DocumentId, Value)>| rows.into_iter().map(|(_, value)| value["name"].clone()).collect::<Vec<_>>();
+        assert_eq!(names(manager.find_by_field_as_of("users", "role", &json!("admin"), before).unwrap()), [json!("Alice")]);
+        assert_eq!(manager.find_by_field("users", "role", &json!("admin")).unwrap().len(), 2);
+        assert_eq!(manager.get_value_as_of("users", &alice, before).unwrap(), Some(json!({"name": "Alice", "role": "admin"})));
+        assert_eq!(manager.get_value("users", &alice).unwrap(), None);
+        assert_eq!(manager.count_as_of("users", before).unwrap(), 2);
+
+        let options = ScanOptions {
+            sort_field: Some("name".to_string()),
+            descending: true,
+            ..Default::default()
+        };
+        assert_eq!(names(manager.list_documents_as_of("users", &options, before).unwrap()), [json!("Bob"), json!("Alice")]);
+    }
+
+    #[test]
+    fn test_transaction_commits_at_one_timestamp() {
+        let manager = create_test_manager();
+        let mut txn = manager.begin_transaction(IsolationLevel::ReadCommitted);
+        let first = txn.insert_value("users", json!({"name": "Alice"})).unwrap();
+        let second = txn.insert_value("users", json!({"name": "Bob"})).unwrap();
+        txn.commit().unwrap();
+
+        let committed_at = manager.storage().read_snapshot(u64::MAX).unwrap();
+        assert_eq!(manager.count_as_of("users", committed_at - 1).unwrap(), 0);
+        assert_eq!(manager.count_as_of("users", committed_at).unwrap(), 2);
+        assert!(manager.get_value_as_of("users", &first, committed_at).unwrap().is_some());
+        assert!(manager.get_value_as_of("users", &second, committed_at).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_as_of_before_retained_history_fails() {
+        use crate::document::DocumentStore;
+        use crate::state::db_interface::Database;
+        use std::time::Duration;
+
+        let manager = create_test_manager();
+        let id = manager.insert_value("users", json!({"name": "Alice"})).unwrap();
+        assert!(matches!(manager.get_value_as_of("users", &id, 0), Err(DocumentError::HistoryUnavailable { requested: 0, .. })));
+
+        let db = Arc::new(Database::new_in_memory().unwrap());
+        let storage = DocumentStore::new(db).with_history_retention(Duration::from_secs(60));
+        let manager = CollectionManager::new(Arc::new(storage));
+        manager.insert_value("users", json!({"name": "Alice"})).unwrap();
+        let now = manager.storage().read_snapshot(u64::MAX).unwrap();
+        assert_eq!(manager.count_as_of("users", now).unwrap(), 1);
+        let outside = now - 120_000_000_000;
+        assert!(matches!(manager.count_as_of("users", outside), Err(DocumentError::HistoryUnavailable { .. })));
+    }
+
+    #[test]
+    fn test_find_by_field_projected() {
+        let manager = create_test_manager();
+        manager
+            .insert_value("users", json!({"name": "Alice", "status": "active", "address": {"city": "Oslo"}, "bio": "x"}))
+            .unwrap();
+        manager.insert_value("users", json!({"name": "Bob", "status": 2, "address": "unknown", "bio": "y"})).unwrap();
+        manager.insert_value("users", json!({"name": "Carol", "status": "active"})).unwrap();
+
+        // Projection applies to reads only: documents the schema rejects are still projected
+        manager.set_schema("users", r#"{"required": ["address"]}"#, false).unwrap();
+
+        let projected = manager.find_by_field_projected("users", "name", &json!("Bob"), &["name", "status", "address.city"]).unwrap();
+        assert_eq!(projected.len(), 1);
+        assert_eq!(projected[0].get("status"), Some(&ProjectedValue::Present(json!(2))));
+        assert_eq!(projected[0].missing(), ["address.city"]);
+        assert_eq!(projected[0].to_value(), json!({"name": "Bob", "status": 2}));
+
+        let active = manager.find_by_field_projected("users", "status", &json!("active"), &["address.city"]).unwrap();
+        let cities: Vec<Value> = active.iter().map(ProjectedDocument::to_value).collect();
+        assert_eq!(cities, [json!({"address": {"city": "Oslo"}}), json!({})]);
+
+        assert!(matches!(
+            manager.find_by_field_projected("users", "name", &json!("Bob"), &["address..city"]),
+            Err(DocumentError::InvalidProjection(_))
+        ));
+    }
+
+    #[test]
+    fn test_projected_find_served_from_covering_index() {
+        use crate::document::DocumentStore;
+        use crate::state::db_interface::{Database, DatabaseInterface};
+
+        let db = Arc::new(Database::new_in_memory().unwrap());
+        let manager = CollectionManager::new(Arc::new(DocumentStore::open(db.clone()).unwrap()));
+        let alice = manager.insert_value("users", json!({"name": "Alice", "status": "active", "body": "large"})).unwrap();
+        manager.insert_value("users", json!({"name": "Bob", "status": "away"})).unwrap();
+        assert!(manager.create_covering_index("users", &["status", "name"]).unwrap());
+        let carol = manager.insert_value("users", json!({"name": "Carol", "status": "active"})).unwrap();
+
+        // Corrupt the document bodies: only a query that never reads them can still succeed
+        for id in [&alice, &carol] {
+            db.put(format!("doc:default:users:{id}").into_bytes(), b"not json".to_vec()).unwrap();
+        }
+        assert!(manager.find_by_field("users", "status", &json!("active")).is_err());
+
+        let active = manager.find_by_field_projected("users", "status", &json!("active"), &["name"]).unwrap();
+        let names: Vec<(DocumentId, Value)> = active.iter().map(|doc| (doc.id.clone(), doc.to_value())).collect();
+        assert_eq!(names, [(alice, json!({"name": "Alice"})), (carol, json!({"name": "Carol"}))]);
+
+        // Paths outside the index fall back to reading documents
+        assert!(manager.find_by_field_projected("users", "status", &json!("active"), &["body"]).is_err());
+        assert!(manager.drop_covering_index("users", &["status", "name"]).unwrap());
+        assert!(manager.find_by_field_projected("users", "status", &json!("active"), &["name"]).is_err());
+    }
+
+    #[test]
+    fn test_schema_enforcement() {
+        let manager = create_test_manager();
+        let bob = manager.insert_value("users", json!({"name": "Bob"})).unwrap();
+        let nameless = manager.insert_value("users", json!({"age": 3})).unwrap();
+
+        let schema = r#"{"type": "object", "required": ["name"], "properties": {"name": {"type": "string"}}}"#;
+        let report = manager.set_schema("users", schema, true).unwrap().unwrap();
+        assert_eq!(report.documents_checked, 2);
+        assert_eq!(report.invalid.len(), 1);
+        assert_eq!(report.invalid[0].id, nameless);
+        assert_eq!(report.invalid[0].violations[0].path, "/name");
+        // Existing violations are reported, not deleted
+        assert!(manager.exists("users", &nameless).unwrap());
+
+        match manager.insert_json("users", r#"{"name": 5}"#) {
+            Err(DocumentError::SchemaViolation { violations, .. }) => {
+                assert_eq!(violations[0].path, "/name");
+                assert_eq!(violations[0].expected, "type string");
+                assert_eq!(violations[0].actual, Some(json!(5)));
+            }
+            other => panic!("expected a schema violation, got {other:?}"),
+        }
+        assert!(manager.update_json("users", &bob, r#"{"nickname": "B"}"#).is_err());
+        assert_eq!(manager.count("users").unwrap(), 2);
+
+        // Trusted writers can bypass the check
+        manager.update_value_with("users", &bob, json!({"nickname": "B"}), Validation::Skip).unwrap();
+        assert_eq!(manager.check_schema("users").unwrap().unwrap().invalid.len(), 2);
+
+        // Other namespaces and managers without a cached copy see the stored schema
+        assert!(manager.with_namespace("other").unwrap().insert_value("users", json!({})).is_ok());
+        let fresh = CollectionManager::new(manager.storage().clone());
+        assert!(fresh.insert_value("users", json!({})).is_err());
+
+        assert!(manager.remove_schema("users").unwrap());
+        manager.insert_value("users", json!({})).unwrap();
+        assert_eq!(manager.check_schema("users").unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid_schema_is_not_stored() {
+        let manager = create_test_manager();
+        assert!(matches!(manager.set_schema("users", r#"{"pattern": "^a"}"#, false), Err(DocumentError::InvalidSchema(_))));
+        assert_eq!(manager.get_schema("users").unwrap(), None);
+    }
+
+    #[test]
+    fn test_compression_codec_changes_and_rewrites() {
+        let manager = create_test_manager();
+        let document = |i: u64| json!({"name": format!("user_{i}"), "status": "active", "roles": ["reader", "writer", "reviewer"], "bio": "lorem ipsum ".repeat(20)});
+        let plain: Vec<_> = (0..10).map(|i| manager.insert_value("users", document(i)).unwrap()).collect();
+        let before = manager.space_usage("users").unwrap();
+        assert_eq!(before.compression_ratio(), 1.0);
+
+        manager.set_compression("users", CompressionCodec::Lz4).unwrap();
+        assert_eq!(manager.get_compression("users").unwrap(), CompressionCodec::Lz4);
+        let compressed = manager.insert_value("users", document(10)).unwrap();
+        // The collection now mixes codecs and reads both
+        assert_eq!(manager.get_value("users", &plain[0]).unwrap(), Some(document(0)));
+        assert_eq!(manager.get_value("users", &compressed).unwrap(), Some(document(10)));
+        assert!(manager.space_usage("users").unwrap().compression_ratio() > 1.0);
+
+        manager.set_compression("users", CompressionCodec::Zstd { level: 3 }).unwrap();
+        let report = manager.spawn_compression_rewrite("users").join().unwrap().unwrap();
+        assert_eq!(report.documents, 11);
+        assert!(report.bytes_after * 2 < report.bytes_before);
+
+        let after = manager.space_usage("users").unwrap();
+        assert_eq!(after.documents, 11);
+        assert!(after.compression_ratio() > 2.0, "ratio {}", after.compression_ratio());
+        for (i, id) in plain.iter().enumerate() {
+            assert_eq!(manager.get_value("users", id).unwrap(), Some(document(i as u64)));
+        }
+        assert_eq!(manager.find_by_field("users", "name", &json!("user_3")).unwrap().len(), 1);
+
+        // Turning compression off leaves stored documents readable
+        manager.set_compression("users", CompressionCodec::None).unwrap();
+        manager.update_value("users", &plain[1], json!({"name": "plain"})).unwrap();
+        assert_eq!(manager.get_value("users", &plain[2]).unwrap(), Some(document(2)));
+        manager.rewrite_compression("users").unwrap();
+        assert_eq!(manager.space_usage("users").unwrap().compression_ratio(), 1.0);
+    }
+
+    #[test]
+    fn test_analyze_source_samples_documents() {
+        let manager = create_test_manager();
+        for i in 0..20 {
+            manager.insert_value("users", json!({"n": i})).unwrap();
+        }
+
+        assert_eq!(manager.row_count("users").unwrap(), 20);
+        let sample = manager.sample_rows("users", 5).unwrap();
+        assert_eq!(sample.len(), 5);
+        let distinct: std::collections::HashSet<_> = sample.iter().map(|row| row["n"].as_i64().unwrap()).collect();
+        assert_eq!(distinct.len(), 5);
+        assert_eq!(manager.sample_rows("users", 100).unwrap().len(), 20);
+    }
+
+    #[tokio::test]
+    async fn test_writes_are_counted_and_refresh_statistics() {
+        use crate::statistics::{AutoRefreshConfig, RefreshStatus, StatisticsConfig};
+
+        let config = StatisticsConfig {
+            auto_refresh: AutoRefreshConfig {
+                min_mutations: 10,
+                min_interval_secs: 0,
+                ..AutoRefreshConfig::default()
+            },
+            ..StatisticsConfig::default()
+        };
+        let collector = Arc::new(StatisticsCollector::new(config));
+        let (root, recording) = create_test_manager().with_statistics(&collector);
+
+        let id = root.insert_value("users", json!({"n": 1})).unwrap();
+        root.insert_value("users", json!({"n": 2})).unwrap();
+        root.update_value("users", &id, json!({"n": 3})).unwrap();
+        assert!(root.delete("users", &id).unwrap());
+        assert!(!root.delete("users", &id).unwrap());
+
+        // Eleven inserts cross the threshold of a collection outside the default namespace
+        root.create_namespace("tenant").unwrap();
+        let tenant = root.with_namespace("tenant").unwrap();
+        for i in 0..11 {
+            tenant.insert_value("orders", json!({"amount": i})).unwrap();
+        }
+        drop((root, tenant));
+        recording.await.unwrap();
+
+        let users = collector.freshness("users").await.unwrap();
+        assert_eq!((users.mutations.inserts, users.mutations.updates, users.mutations.deletes), (2, 1, 1));
+        assert_eq!(users.row_count, 1);
+
+        let mut orders = collector.freshness("tenant.orders").await.unwrap();
+        for _ in 0..200 {
+            if orders.last_refresh.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            orders = collector.freshness("tenant.orders").await.unwrap();
+        }
+        let outcome = orders.last_refresh.expect("refresh never ran");
+        assert_eq!(outcome.status, RefreshStatus::Completed);
+        assert_eq!((outcome.row_count, outcome.sampled_rows), (11, 11));
+    }
+
     #[test]
     fn test_showcase_scenario() {
         let manager = create_test_manager();
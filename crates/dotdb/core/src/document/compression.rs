@@ -0,0 +1,240 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Compression of stored document bodies
+//!
+//! Every collection has a codec that the documents written to it are compressed
+//! with. A compressed body is stored as a frame:
+//!
+//! - a zero byte, which no JSON text starts with
+//! - the codec's tag, `1` for LZ4 and `2` for Zstd
+//! - the uncompressed length, a little-endian `u32`
+//! - the compressed bytes
+//!
+//! Bodies without the leading zero byte are plain JSON. Documents written before
+//! a collection had a codec, or under [`CompressionCodec::None`], therefore read back
+//! unchanged, and a collection whose codec changed reads its older documents with
+//! the codec they were written with.
+
+use super::{DocumentError, DocumentResult};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+/// First byte of a compressed body
+const FRAME_MARKER: u8 = 0;
+
+/// Bytes before the compressed payload: marker, tag and uncompressed length
+const FRAME_HEADER_LEN: usize = 6;
+
+const LZ4_TAG: u8 = 1;
+const ZSTD_TAG: u8 = 2;
+
+/// Codec a collection compresses its document bodies with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum CompressionCodec {
+    /// Bodies are stored as plain JSON
+    #[default]
+    None,
+    /// Fast compression with a modest ratio
+    Lz4,
+    /// Slower compression with a better ratio; higher levels trade write speed for size
+    Zstd { level: i32 },
+}
+
+impl CompressionCodec {
+    /// Zstd level used when none is given
+    pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+    /// Highest accepted Zstd level
+    pub const MAX_ZSTD_LEVEL: i32 = 22;
+
+    /// Encode a serialized body for storage
+    ///
+    /// A body that does not get smaller is stored uncompressed, so reading it costs
+    /// nothing extra.
+    pub fn encode(self, body: Vec<u8>) -> DocumentResult<Vec<u8>> {
+        let (tag, compressed) = match self {
+            CompressionCodec::None => return Ok(body),
+            CompressionCodec::Lz4 => (LZ4_TAG, lz4_flex::block::compress(&body)),
+            CompressionCodec::Zstd { level } => (ZSTD_TAG, zstd::bulk::compress(&body, level).map_err(|e| DocumentError::Compression(e.to_string()))?),
+        };
+        let Ok(len) = u32::try_from(body.len()) else {
+            return Ok(body);
+        };
+        if FRAME_HEADER_LEN + compressed.len() >= body.len() {
+            return Ok(body);
+        }
+
+        let mut framed = Vec::with_capacity(FRAME_HEADER_LEN + compressed.len());
+        framed.push(FRAME_MARKER);
+        framed.push(tag);
+        framed.extend_from_slice(&len.to_le_bytes());
+        framed.extend_from_slice(&compressed);
+        Ok(framed)
+    }
+}
+
+impl fmt::Display for CompressionCodec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompressionCodec::None => write!(f, "none"),
+            CompressionCodec::Lz4 => write!(f, "lz4"),
+            CompressionCodec::Zstd { level } => write!(f, "zstd:{level}"),
+        }
+    }
+}
+
+impl FromStr for CompressionCodec {
+    type Err = DocumentError;
+
+    /// Parse `none`, `lz4`, `zstd` or `zstd:<level>` with a level from 1 to 22
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || DocumentError::InvalidCodec(format!("{s} (expected none, lz4, zstd or zstd:<level> with a level from 1 to {})", Self::MAX_ZSTD_LEVEL));
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(CompressionCodec::None),
+            "lz4" => Ok(CompressionCodec::Lz4),
+            "zstd" => Ok(CompressionCodec::Zstd { level: Self::DEFAULT_ZSTD_LEVEL }),
+            other => {
+                let level = other.strip_prefix("zstd:").and_then(|level| level.parse::<i32>().ok()).ok_or_else(invalid)?;
+                if !(1..=Self::MAX_ZSTD_LEVEL).contains(&level) {
+                    return Err(invalid());
+                }
+                Ok(CompressionCodec::Zstd { level })
+            }
+        }
+    }
+}
+
+impl From<CompressionCodec> for String {
+    fn from(codec: CompressionCodec) -> Self {
+        codec.to_string()
+    }
+}
+
+impl TryFrom<String> for CompressionCodec {
+    type Error = DocumentError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// What rewriting documents under their collection's current codec did
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecompressionReport {
+    pub documents: u64,
+    /// Stored size of the rewritten documents and their histories before the rewrite
+    pub bytes_before: u64,
+    /// Stored size of the same values after it
+    pub bytes_after: u64,
+}
+
+impl RecompressionReport {
+    /// Add the results of another batch
+    pub fn merge(&mut self, other: RecompressionReport) {
+        self.documents += other.documents;
+        self.bytes_before += other.bytes_before;
+        self.bytes_after += other.bytes_after;
+    }
+}
+
+/// The serialized body of a stored value, decompressed if it was compressed
+pub fn decode(data: &[u8]) -> DocumentResult<Cow<'_, [u8]>> {
+    let Some((tag, len, payload)) = frame(data)? else {
+        return Ok(Cow::Borrowed(data));
+    };
+    let body = match tag {
+        LZ4_TAG => lz4_flex::block::decompress(payload, len).map_err(|e| DocumentError::Compression(e.to_string()))?,
+        ZSTD_TAG => zstd::bulk::decompress(payload, len).map_err(|e| DocumentError::Compression(e.to_string()))?,
+        other => return Err(DocumentError::Compression(format!("unknown codec tag {other}"))),
+    };
+    if body.len() != len {
+        return Err(DocumentError::Compression(format!("body decompressed to {} bytes, its header records {len}", body.len())));
+    }
+    Ok(Cow::Owned(body))
+}
+
+/// Size of a stored value's body once decompressed, read from its header without
+/// decompressing it
+pub fn uncompressed_len(data: &[u8]) -> DocumentResult<usize> {
+    Ok(frame(data)?.map_or(data.len(), |(_, len, _)| len))
+}
+
+/// Tag, uncompressed length and payload of a compressed body; `None` for plain JSON
+fn frame(data: &[u8]) -> DocumentResult<Option<(u8, usize, &[u8])>> {
+    if data.first() != Some(&FRAME_MARKER) {
+        return Ok(None);
+    }
+    if data.len() < FRAME_HEADER_LEN {
+        return Err(DocumentError::Compression(format!("truncated frame header of {} bytes", data.len())));
+    }
+    let len = u32::from_le_bytes(data[2..FRAME_HEADER_LEN].try_into().unwrap()) as usize;
+    Ok(Some((data[1], len, &data[FRAME_HEADER_LEN..])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verbose_json() -> Vec<u8> {
+        let items: Vec<_> = (0..50)
+            .map(|i| serde_json::json!({"name": format!("item_{i}"), "status": "active", "tags": ["alpha", "beta"]}))
+            .collect();
+        serde_json::to_vec(&items).unwrap()
+    }
+
+    #[test]
+    fn test_codecs_round_trip() {
+        let body = verbose_json();
+        for codec in [CompressionCodec::None, CompressionCodec::Lz4, CompressionCodec::Zstd { level: 3 }, CompressionCodec::Zstd { level: 19 }] {
+            let stored = codec.encode(body.clone()).unwrap();
+            if codec != CompressionCodec::None {
+                assert!(stored.len() < body.len() / 2, "{codec} stored {} of {} bytes", stored.len(), body.len());
+            }
+            assert_eq!(uncompressed_len(&stored).unwrap(), body.len());
+            assert_eq!(decode(&stored).unwrap().as_ref(), body.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_incompressible_bodies_are_stored_plain() {
+        let body = br#"{"a":1}"#.to_vec();
+        assert_eq!(CompressionCodec::Lz4.encode(body.clone()).unwrap(), body);
+        assert!(matches!(decode(&body).unwrap(), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_corrupt_frames_are_rejected() {
+        let mut stored = CompressionCodec::Lz4.encode(verbose_json()).unwrap();
+        assert!(matches!(decode(&stored[..3]), Err(DocumentError::Compression(_))));
+        stored[1] = 9;
+        assert!(matches!(decode(&stored), Err(DocumentError::Compression(_))));
+    }
+
+    #[test]
+    fn test_codec_names() {
+        assert_eq!("zstd".parse::<CompressionCodec>().unwrap(), CompressionCodec::Zstd { level: 3 });
+        assert_eq!("ZSTD:19".parse::<CompressionCodec>().unwrap(), CompressionCodec::Zstd { level: 19 });
+        assert_eq!("lz4".parse::<CompressionCodec>().unwrap().to_string(), "lz4");
+        for invalid in ["gzip", "zstd:0", "zstd:23", "zstd:fast"] {
+            assert!(matches!(invalid.parse::<CompressionCodec>(), Err(DocumentError::InvalidCodec(_))), "{invalid}");
+        }
+        assert_eq!(serde_json::to_string(&CompressionCodec::Zstd { level: 5 }).unwrap(), r#""zstd:5""#);
+    }
+}
@@ -0,0 +1,271 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Document version history for time-travel reads
+//!
+//! Every write through a [`DocumentStore`](super::DocumentStore) appends the
+//! document's new version, or a tombstone when it is deleted, to the document's
+//! history under the commit's timestamp. All writes of one commit share a
+//! timestamp: a transaction's writes are one commit, and so is each write made
+//! outside a transaction. A read as of time `t` sees, for every document, the
+//! latest version committed at or before `t`.
+//!
+//! Commit timestamps are nanoseconds since the Unix epoch from the storage
+//! engine's clock ([`generate_timestamp`]), kept strictly increasing. A
+//! time-travel query first fixes its snapshot with
+//! [`read_snapshot`](super::DocumentStorage::read_snapshot), which waits for the
+//! commit in flight and is never later than the last completed commit, so every
+//! document the query reads resolves against the same set of commits.
+//!
+//! History starts when a database is first opened with history support: the
+//! horizon is set to that time and reads as of earlier times fail with
+//! [`DocumentError::HistoryUnavailable`](super::DocumentError::HistoryUnavailable).
+//! With a retention window, versions that fell out of it are pruned the next
+//! time their document is written and the horizon moves forward with the window.
+//!
+//! A [`SnapshotPin`] holds the horizon back at its snapshot for as long as it is
+//! held, so reads through a long-running read snapshot keep resolving however
+//! much the retention window or a compaction would otherwise prune. A pin held
+//! past its maximum age is released by force the next time pins are consulted,
+//! with a warning, so a forgotten handle cannot block pruning forever.
+
+use super::Document;
+use crate::storage_engine::generate_timestamp;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock, RwLockWriteGuard};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Storage key of the earliest time history is kept from
+pub(crate) const HISTORY_HORIZON_KEY: &[u8] = b"history_horizon";
+
+/// A document as committed at one point in time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct DocumentVersion {
+    /// Commit timestamp, in nanoseconds since the Unix epoch
+    pub committed_at: u64,
+    /// `None` records that the document was deleted
+    pub document: Option<Document>,
+}
+
+/// The version visible at `snapshot` of a history ordered by commit timestamp
+pub(crate) fn visible_at(versions: &[DocumentVersion], snapshot: u64) -> Option<&DocumentVersion> {
+    versions.iter().rev().find(|version| version.committed_at <= snapshot)
+}
+
+/// Drop versions no read at or after `horizon` can see
+///
+/// The latest version committed at or before the horizon is what such reads see
+/// until the next version, so it is kept unless it is a tombstone.
+pub(crate) fn prune(versions: &mut Vec<DocumentVersion>, horizon: u64) {
+    let Some(base) = versions.iter().rposition(|version| version.committed_at <= horizon) else {
+        return;
+    };
+    let keep_from = if versions[base].document.is_some() { base } else { base + 1 };
+    versions.drain(..keep_from);
+}
+
+/// Hands out commit timestamps and fixes read snapshots
+///
+/// Holds the timestamp of the last completed commit. Commits hold the lock for
+/// writing while they apply, so they run one at a time and a snapshot is only
+/// taken between them.
+#[derive(Debug)]
+pub(crate) struct HistoryClock {
+    last_committed: RwLock<u64>,
+    pins: Arc<SnapshotPins>,
+}
+
+impl HistoryClock {
+    pub(crate) fn new() -> Self {
+        Self {
+            last_committed: RwLock::new(generate_timestamp()),
+            pins: Arc::new(SnapshotPins::default()),
+        }
+    }
+
+    /// Start a commit, waiting for the one in flight
+    pub(crate) fn begin(&self) -> Commit<'_> {
+        let last_committed = self.last_committed.write().unwrap_or_else(|e| e.into_inner());
+        let timestamp = generate_timestamp().max(*last_committed + 1);
+        Commit { last_committed, timestamp }
+    }
+
+    /// Snapshot for a read as of `as_of`, no later than the last completed commit
+    pub(crate) fn snapshot(&self, as_of: u64) -> u64 {
+        as_of.min(*self.last_committed.read().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Pin a snapshot of the last completed commit for at most `max_age`
+    ///
+    /// The snapshot is pinned before the next commit can start, so no commit prunes
+    /// what it can see.
+    pub(crate) fn pin(&self, max_age: Duration) -> SnapshotPin {
+        let last_committed = self.last_committed.read().unwrap_or_else(|e| e.into_inner());
+        let id = self.pins.pin(*last_committed, max_age);
+        SnapshotPin {
+            id,
+            snapshot: *last_committed,
+            pins: self.pins.clone(),
+        }
+    }
+
+    /// Snapshot of the oldest pin still held
+    pub(crate) fn oldest_pin(&self) -> Option<u64> {
+        self.pins.oldest()
+    }
+}
+
+/// A pinned snapshot
+#[derive(Debug, Clone, Copy)]
+struct Pin {
+    snapshot: u64,
+    pinned_at: Instant,
+    max_age: Duration,
+}
+
+/// Snapshots held by [`SnapshotPin`]s, by pin ID
+#[derive(Debug, Default)]
+pub(crate) struct SnapshotPins {
+    pins: Mutex<BTreeMap<u64, Pin>>,
+    next_id: AtomicU64,
+}
+
+impl SnapshotPins {
+    fn pin(&self, snapshot: u64, max_age: Duration) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let pin = Pin {
+            snapshot,
+            pinned_at: Instant::now(),
+            max_age,
+        };
+        self.pins.lock().unwrap_or_else(|e| e.into_inner()).insert(id, pin);
+        id
+    }
+
+    /// Pins still held, after releasing those past their maximum age
+    fn held(&self) -> std::sync::MutexGuard<'_, BTreeMap<u64, Pin>> {
+        let mut pins = self.pins.lock().unwrap_or_else(|e| e.into_inner());
+        pins.retain(|id, pin| {
+            let age = pin.pinned_at.elapsed();
+            if age <= pin.max_age {
+                return true;
+            }
+            warn!(pin = id, snapshot = pin.snapshot, age_secs = age.as_secs(), "Read snapshot held past its maximum age; releasing it");
+            false
+        });
+        pins
+    }
+
+    fn oldest(&self) -> Option<u64> {
+        self.held().values().map(|pin| pin.snapshot).min()
+    }
+}
+
+/// A snapshot kept readable until released
+///
+/// History is not pruned past a held pin. Dropping the pin releases it, as does
+/// holding it past the maximum age it was pinned with.
+#[derive(Debug)]
+pub struct SnapshotPin {
+    id: u64,
+    snapshot: u64,
+    pins: Arc<SnapshotPins>,
+}
+
+impl SnapshotPin {
+    /// The pinned snapshot, in nanoseconds since the Unix epoch
+    pub fn snapshot(&self) -> u64 {
+        self.snapshot
+    }
+
+    /// Whether the pin is still held, rather than released by force for its age
+    pub fn is_held(&self) -> bool {
+        self.pins.held().contains_key(&self.id)
+    }
+
+    /// Release the pin, returning whether it was still held
+    pub fn release(self) -> bool {
+        self.pins.pins.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.id).is_some()
+    }
+}
+
+impl Drop for SnapshotPin {
+    fn drop(&mut self) {
+        self.pins.pins.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.id);
+    }
+}
+
+/// A commit in progress; completes when dropped
+pub(crate) struct Commit<'a> {
+    last_committed: RwLockWriteGuard<'a, u64>,
+    pub timestamp: u64,
+}
+
+impl Drop for Commit<'_> {
+    fn drop(&mut self) {
+        *self.last_committed = self.timestamp;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn version(committed_at: u64, content: Option<serde_json::Value>) -> DocumentVersion {
+        DocumentVersion {
+            committed_at,
+            document: content.map(Document::new),
+        }
+    }
+
+    #[test]
+    fn test_visible_version_and_pruning() {
+        let mut versions = vec![version(10, Some(json!(1))), version(20, Some(json!(2))), version(30, None), version(40, Some(json!(4)))];
+        let content = |versions: &[DocumentVersion], at| visible_at(versions, at).and_then(|v| v.document.as_ref()).map(|d| d.content.clone());
+        assert_eq!(content(&versions, 5), None);
+        assert_eq!(content(&versions, 25), Some(json!(2)));
+        assert_eq!(content(&versions, 35), None);
+        assert_eq!(content(&versions, 45), Some(json!(4)));
+
+        // The version visible at the horizon survives, older ones go
+        prune(&mut versions, 25);
+        assert_eq!(versions.iter().map(|v| v.committed_at).collect::<Vec<_>>(), [20, 30, 40]);
+        assert_eq!(content(&versions, 25), Some(json!(2)));
+
+        // A tombstone at the horizon is implied by the absence of older versions
+        prune(&mut versions, 35);
+        assert_eq!(versions.iter().map(|v| v.committed_at).collect::<Vec<_>>(), [40]);
+        assert_eq!(content(&versions, 35), None);
+    }
+
+    #[test]
+    fn test_snapshots_never_pass_the_last_commit() {
+        let clock = HistoryClock::new();
+        let before = clock.snapshot(u64::MAX);
+        let commit = clock.begin();
+        let timestamp = commit.timestamp;
+        assert!(timestamp > before);
+        drop(commit);
+
+        assert_eq!(clock.snapshot(u64::MAX), timestamp);
+        assert_eq!(clock.snapshot(before), before);
+        assert!(clock.begin().timestamp > timestamp);
+    }
+}
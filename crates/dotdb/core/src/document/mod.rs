@@ -18,20 +18,39 @@
 //!
 //! This module provides a document-oriented abstraction over the key-value
 //! database interface. It supports JSON documents organized into collections
-//! with UUID-based document identification.
+//! with UUID-based document identification. Collections live in namespaces so
+//! several tenants can share one database without prefixing collection names.
 
+pub mod aggregation;
 pub mod collection;
+pub mod compression;
+mod history;
+pub mod projection;
+pub mod read_snapshot;
+pub mod replication;
+pub mod schema;
+pub mod snapshot;
 pub mod storage;
+pub mod transaction;
 
+pub use aggregation::{Aggregate, AggregateFunction, AggregateGroup, AggregationResult, AggregationSpec, AggregationWarnings, TimeUnit, TimeWindow, WindowSize, timestamp_key};
 pub use collection::*;
+pub use compression::{CompressionCodec, RecompressionReport};
+pub use history::SnapshotPin;
+pub use projection::{ProjectedDocument, ProjectedValue, Projection};
+pub use read_snapshot::ReadSnapshot;
+pub use replication::{ChangeFeed, ChangeFeedStorage, ChangeOp, ChangeRecord, Replica, ReplicationLag};
+pub use schema::{DocumentViolations, JsonSchema, SchemaReport, SchemaViolation, Validation};
+pub use snapshot::{SnapshotSummary, export_snapshot, import_snapshot};
 pub use storage::*;
+pub use transaction::{AbortReason, DocumentTransaction};
 
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use uuid::Uuid;
 
 /// Document identifier using UUID
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct DocumentId(pub Uuid);
 
 impl DocumentId {
@@ -114,6 +133,50 @@ impl From<&str> for CollectionName {
     }
 }
 
+/// Namespace isolating one tenant's collections from another's
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Namespace(String);
+
+impl Namespace {
+    /// Namespace that data written before namespaces existed belongs to
+    pub const DEFAULT: &'static str = "default";
+
+    /// Longest accepted namespace name in bytes
+    pub const MAX_LEN: usize = 64;
+
+    /// Create a namespace, accepting only ASCII letters, digits, `_` and `-` so the
+    /// name can never be confused with the `:` separators of storage keys
+    pub fn new(name: impl Into<String>) -> DocumentResult<Self> {
+        let name = name.into();
+        if name.is_empty() || name.len() > Self::MAX_LEN || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(DocumentError::InvalidNamespace(name));
+        }
+        Ok(Self(name))
+    }
+
+    /// Get the string value
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether this is the default namespace
+    pub fn is_default(&self) -> bool {
+        self.0 == Self::DEFAULT
+    }
+}
+
+impl Default for Namespace {
+    fn default() -> Self {
+        Self(Self::DEFAULT.to_string())
+    }
+}
+
+impl fmt::Display for Namespace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 /// JSON document value
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Document {
@@ -207,6 +270,9 @@ pub enum DocumentError {
     #[error("Collection not found: {0}")]
     CollectionNotFound(CollectionName),
 
+    #[error("Collection already exists: {0}")]
+    CollectionAlreadyExists(CollectionName),
+
     #[error("Invalid document ID: {0}")]
     InvalidDocumentId(String),
 
@@ -215,6 +281,77 @@ pub enum DocumentError {
 
     #[error("Document already exists: {0}")]
     DocumentAlreadyExists(DocumentId),
+
+    #[error("Document {id} is at version {current}, not the expected version {expected}")]
+    VersionConflict { id: DocumentId, expected: u64, current: u64 },
+
+    #[error("Invalid namespace: {0}")]
+    InvalidNamespace(String),
+
+    #[error("Namespace {0} still has collections; drop them first or force the drop")]
+    NamespaceNotEmpty(Namespace),
+
+    #[error("Index error: {0}")]
+    Index(#[from] crate::indices::IndexError),
+
+    #[error("Storage error: {0}")]
+    Storage(#[from] crate::storage_engine::StorageError),
+
+    #[error("Statistics error: {0}")]
+    Statistics(#[from] crate::statistics::StatisticsError),
+
+    #[error("Invalid schema: {0}")]
+    InvalidSchema(String),
+
+    #[error("Invalid projection: {0}")]
+    InvalidProjection(String),
+
+    #[error("Invalid aggregation: {0}")]
+    InvalidAggregation(String),
+
+    #[error("Invalid compression codec: {0}")]
+    InvalidCodec(String),
+
+    #[error("Compressed document is corrupt: {0}")]
+    Compression(String),
+
+    #[error("Document does not match the schema of {collection}: {}", schema::summarize(.violations))]
+    SchemaViolation { collection: CollectionName, violations: Vec<SchemaViolation> },
+
+    #[error("Transaction {txn_id} aborted: {reason}")]
+    TransactionAborted { txn_id: u64, reason: transaction::AbortReason },
+
+    #[error("Invalid snapshot: {0}")]
+    InvalidSnapshot(String),
+
+    #[error("Snapshot I/O error: {0}")]
+    SnapshotIo(#[from] std::io::Error),
+
+    #[error("This is a read-only replica; send writes to the primary at {primary}")]
+    ReadOnlyReplica { primary: String },
+
+    #[error("Replica has applied changes up to sequence {applied}, not yet {required}")]
+    ReplicaBehind { applied: u64, required: u64 },
+
+    #[error("Change feed no longer holds sequence {requested}; the oldest it holds is {oldest}")]
+    ChangeFeedTruncated { requested: u64, oldest: u64 },
+
+    #[error("Replication gap: expected change {expected}, received {received}")]
+    ReplicationGap { expected: u64, received: u64 },
+
+    #[error("History is kept from {horizon} ns since the Unix epoch; cannot read as of {requested}")]
+    HistoryUnavailable { requested: u64, horizon: u64 },
+
+    #[error("Read snapshot at {snapshot} has been released")]
+    SnapshotReleased { snapshot: u64 },
+}
+
+impl DocumentError {
+    /// Whether the operation failed because of a concurrent transaction and can be
+    /// retried in a new transaction
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::TransactionAborted { .. })
+    }
 }
 
 /// Type alias for document operation results
@@ -245,6 +382,15 @@ mod tests {
         assert_eq!(name.to_string(), "users");
     }
 
+    #[test]
+    fn test_namespace_validation() {
+        assert_eq!(Namespace::new("tenant-a_1").unwrap().as_str(), "tenant-a_1");
+        assert!(Namespace::default().is_default());
+        for invalid in ["", "a:b", "a b", "ünï", &"x".repeat(Namespace::MAX_LEN + 1)] {
+            assert!(matches!(Namespace::new(invalid), Err(DocumentError::InvalidNamespace(_))), "{invalid:?} accepted");
+        }
+    }
+
     #[test]
     fn test_document_creation() {
         let content = serde_json::json!({"name": "Alice", "age": 30});
@@ -0,0 +1,206 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Field projection
+//!
+//! A projection names the paths a reader needs from each document, so queries can
+//! return those instead of whole documents. Paths are dot-separated: `address.city`
+//! selects `city` inside the `address` object, and a numeric segment such as
+//! `tags.0` selects an array element. A path a document does not have (including
+//! one that runs into a value of another type, like `address.city` when `address`
+//! is a string) is reported as [`ProjectedValue::Missing`], which is distinct from
+//! a path holding `null`.
+
+use super::{DocumentError, DocumentId, DocumentResult};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Value of one projected path in a document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ProjectedValue {
+    Present(Value),
+    /// The document has nothing at this path
+    Missing,
+}
+
+/// A requested path with its segments
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FieldPath {
+    path: String,
+    segments: Vec<String>,
+}
+
+impl FieldPath {
+    fn parse(path: &str) -> DocumentResult<Self> {
+        let path = path.trim();
+        let segments: Vec<String> = path.split('.').map(str::to_string).collect();
+        if segments.iter().any(String::is_empty) {
+            return Err(DocumentError::InvalidProjection(format!("'{path}' is not a field path")));
+        }
+        Ok(Self { path: path.to_string(), segments })
+    }
+
+    fn lookup<'a>(&self, content: &'a Value) -> Option<&'a Value> {
+        self.segments.iter().try_fold(content, |value, segment| match value {
+            Value::Object(map) => map.get(segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|index| items.get(index)),
+            _ => None,
+        })
+    }
+}
+
+/// The paths a query returns from each document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Projection {
+    paths: Vec<FieldPath>,
+}
+
+impl Projection {
+    /// Project `paths`, in the order given; repeated paths are kept once
+    pub fn new<S: AsRef<str>>(paths: &[S]) -> DocumentResult<Self> {
+        let mut parsed: Vec<FieldPath> = Vec::with_capacity(paths.len());
+        for path in paths {
+            let path = FieldPath::parse(path.as_ref())?;
+            if !parsed.contains(&path) {
+                parsed.push(path);
+            }
+        }
+        if parsed.is_empty() {
+            return Err(DocumentError::InvalidProjection("no fields requested".to_string()));
+        }
+        Ok(Self { paths: parsed })
+    }
+
+    /// Parse a comma-separated path list such as `name,status,address.city`
+    pub fn parse(list: &str) -> DocumentResult<Self> {
+        Self::new(&list.split(',').collect::<Vec<_>>())
+    }
+
+    /// Projected paths, in order
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.paths.iter().map(|path| path.path.as_str())
+    }
+
+    /// Value of each projected path in `content`, in path order
+    pub fn project(&self, content: &Value) -> Vec<ProjectedValue> {
+        self.paths
+            .iter()
+            .map(|path| match path.lookup(content) {
+                Some(value) => ProjectedValue::Present(value.clone()),
+                None => ProjectedValue::Missing,
+            })
+            .collect()
+    }
+
+    /// Whether every projected path is among `indexed`
+    pub fn is_covered_by(&self, indexed: &[String]) -> bool {
+        self.paths().all(|path| indexed.iter().any(|indexed| indexed == path))
+    }
+}
+
+/// A document reduced to the paths of a projection
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectedDocument {
+    pub id: DocumentId,
+    /// Each projected path with its value, in projection order
+    pub fields: Vec<(String, ProjectedValue)>,
+}
+
+impl ProjectedDocument {
+    /// Value of a projected path, or `None` if the path was not projected
+    pub fn get(&self, path: &str) -> Option<&ProjectedValue> {
+        self.fields.iter().find(|(projected, _)| projected == path).map(|(_, value)| value)
+    }
+
+    /// Projected paths the document does not have
+    pub fn missing(&self) -> Vec<&str> {
+        self.fields.iter().filter(|(_, value)| *value == ProjectedValue::Missing).map(|(path, _)| path.as_str()).collect()
+    }
+
+    /// The present paths nested back into a JSON object; missing paths are left out
+    ///
+    /// Array elements selected by index appear under their index as an object key.
+    pub fn to_value(&self) -> Value {
+        let mut root = Map::new();
+        for (path, value) in &self.fields {
+            if let ProjectedValue::Present(value) = value {
+                let segments: Vec<&str> = path.split('.').collect();
+                insert_path(&mut root, &segments, value.clone());
+            }
+        }
+        Value::Object(root)
+    }
+}
+
+fn insert_path(root: &mut Map<String, Value>, segments: &[&str], value: Value) {
+    let Some((last, parents)) = segments.split_last() else {
+        return;
+    };
+
+    let mut node = root;
+    for segment in parents {
+        let entry = node.entry(segment.to_string()).or_insert_with(|| Value::Object(Map::new()));
+        // An ancestor projected whole already holds this value
+        let Value::Object(child) = entry else {
+            return;
+        };
+        node = child;
+    }
+    node.insert(last.to_string(), value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_nested_and_missing_paths() {
+        let projection = Projection::parse("name, address.city,tags.1,nickname,name").unwrap();
+        assert_eq!(projection.paths().collect::<Vec<_>>(), ["name", "address.city", "tags.1", "nickname"]);
+
+        let content = json!({"name": "Ada", "address": {"city": "London", "zip": "N1"}, "tags": ["a", "b"], "body": "x"});
+        let document = ProjectedDocument {
+            id: DocumentId::new(),
+            fields: projection.paths().map(str::to_string).zip(projection.project(&content)).collect(),
+        };
+
+        assert_eq!(document.get("address.city"), Some(&ProjectedValue::Present(json!("London"))));
+        assert_eq!(document.missing(), ["nickname"]);
+        assert_eq!(document.to_value(), json!({"name": "Ada", "address": {"city": "London"}, "tags": {"1": "b"}}));
+    }
+
+    #[test]
+    fn test_paths_through_other_types_are_missing() {
+        let projection = Projection::new(&["address.city", "status"]).unwrap();
+
+        assert_eq!(
+            projection.project(&json!({"address": "12 Main St", "status": 3})),
+            [ProjectedValue::Missing, ProjectedValue::Present(json!(3))]
+        );
+        assert_eq!(
+            projection.project(&json!({"address": {"city": null}, "status": "active"})),
+            [ProjectedValue::Present(Value::Null), ProjectedValue::Present(json!("active"))]
+        );
+    }
+
+    #[test]
+    fn test_rejects_empty_paths() {
+        assert!(Projection::parse("name,,status").is_err());
+        assert!(Projection::new(&["address."]).is_err());
+        assert!(Projection::new::<&str>(&[]).is_err());
+    }
+}
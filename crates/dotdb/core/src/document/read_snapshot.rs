@@ -0,0 +1,198 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Read Snapshots
+//!
+//! A [`ReadSnapshot`] runs read-only queries, such as an analytical report joining
+//! several collections, against one point in time while writes continue. It pins
+//! that point so neither the retention window nor compaction prunes the history
+//! its reads need, which makes it worth releasing as soon as the queries are done.
+
+use super::aggregation::{AggregationResult, AggregationSpec};
+use super::history::SnapshotPin;
+use super::{CollectionManager, CollectionName, DocumentError, DocumentId, DocumentResult, ScanOptions};
+use serde_json::Value;
+use std::fmt;
+
+/// Consistent view of every collection of a namespace, from
+/// [`CollectionManager::begin_read_snapshot`]
+///
+/// Reads fail with [`DocumentError::SnapshotReleased`] once the snapshot has been
+/// held past its maximum age and released by force. Dropping the handle releases it.
+pub struct ReadSnapshot {
+    manager: CollectionManager,
+    pin: SnapshotPin,
+}
+
+impl ReadSnapshot {
+    pub(super) fn new(manager: CollectionManager, pin: SnapshotPin) -> Self {
+        Self { manager, pin }
+    }
+
+    /// Time the snapshot was taken at, in nanoseconds since the Unix epoch
+    pub fn timestamp(&self) -> u64 {
+        self.pin.snapshot()
+    }
+
+    /// Namespace the snapshot reads from
+    pub fn namespace(&self) -> &str {
+        self.manager.namespace()
+    }
+
+    /// Whether reads can still go through the snapshot
+    pub fn is_active(&self) -> bool {
+        self.pin.is_held()
+    }
+
+    /// Get a document as JSON value
+    pub fn get_value(&self, collection: &str, id: &DocumentId) -> DocumentResult<Option<Value>> {
+        let snapshot = self.snapshot()?;
+        Ok(self.manager.read_document(&CollectionName::new(collection), id, Some(snapshot))?.map(|document| document.content))
+    }
+
+    /// List the IDs of a collection's documents
+    pub fn list_document_ids(&self, collection: &str) -> DocumentResult<Vec<DocumentId>> {
+        let snapshot = self.snapshot()?;
+        self.manager.document_ids(&CollectionName::new(collection), Some(snapshot))
+    }
+
+    /// List a collection's documents in the order and window given by `options`
+    pub fn list_documents(&self, collection: &str, options: &ScanOptions) -> DocumentResult<Vec<(DocumentId, Value)>> {
+        let snapshot = self.snapshot()?;
+        self.manager.scan_documents(collection, options, Some(snapshot))
+    }
+
+    /// Count a collection's documents
+    pub fn count(&self, collection: &str) -> DocumentResult<usize> {
+        Ok(self.list_document_ids(collection)?.len())
+    }
+
+    /// Find documents whose top-level `field` equals `value`
+    pub fn find_by_field(&self, collection: &str, field: &str, value: &Value) -> DocumentResult<Vec<(DocumentId, Value)>> {
+        let snapshot = self.snapshot()?;
+        self.manager.match_field(collection, field, value, Some(snapshot))
+    }
+
+    /// Group a collection's documents and aggregate each group as `spec` describes
+    pub fn aggregate(&self, collection: &str, spec: &AggregationSpec) -> DocumentResult<AggregationResult> {
+        let snapshot = self.snapshot()?;
+        self.manager.aggregate_at(collection, spec, Some(snapshot))
+    }
+
+    /// Release the snapshot, returning whether it was still held
+    ///
+    /// A snapshot released by force for its age returns `false`.
+    pub fn release(self) -> bool {
+        self.pin.release()
+    }
+
+    fn snapshot(&self) -> DocumentResult<u64> {
+        if !self.pin.is_held() {
+            return Err(DocumentError::SnapshotReleased { snapshot: self.pin.snapshot() });
+        }
+        Ok(self.pin.snapshot())
+    }
+}
+
+impl fmt::Debug for ReadSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadSnapshot").field("namespace", &self.namespace()).field("timestamp", &self.timestamp()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compaction::strategy::DeadSpaceStrategy;
+    use crate::document::{DocumentStore, create_in_memory_collection_manager};
+    use crate::state::db_interface::Database;
+    use serde_json::json;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// A manager that keeps no history beyond what pinned snapshots need
+    fn manager_without_retention() -> CollectionManager {
+        let db = Arc::new(Database::new_in_memory().unwrap());
+        CollectionManager::new(Arc::new(DocumentStore::new(db).with_history_retention(Duration::ZERO)))
+    }
+
+    #[test]
+    fn test_writes_after_begin_are_invisible() {
+        let manager = create_in_memory_collection_manager().unwrap();
+        let alice = manager.insert_value("users", json!({"name": "Alice", "team": "core"})).unwrap();
+        manager.insert_value("orders", json!({"user": "Alice", "total": 10})).unwrap();
+
+        let snapshot = manager.begin_read_snapshot().unwrap();
+        manager.update_value("users", &alice, json!({"name": "Alice", "team": "infra"})).unwrap();
+        manager.insert_value("users", json!({"name": "Bob", "team": "core"})).unwrap();
+        manager.insert_value("orders", json!({"user": "Bob", "total": 32})).unwrap();
+        manager.create_collection("audit").unwrap();
+        manager.insert_value("audit", json!({"event": "signup"})).unwrap();
+
+        assert_eq!(snapshot.get_value("users", &alice).unwrap(), Some(json!({"name": "Alice", "team": "core"})));
+        assert_eq!(snapshot.count("users").unwrap(), 1);
+        assert_eq!(snapshot.list_document_ids("orders").unwrap().len(), 1);
+        assert_eq!(snapshot.count("audit").unwrap(), 0);
+        assert_eq!(snapshot.find_by_field("users", "team", &json!("core")).unwrap().len(), 1);
+        assert!(snapshot.find_by_field("users", "team", &json!("infra")).unwrap().is_empty());
+        let listed = snapshot.list_documents("orders", &ScanOptions::default()).unwrap();
+        assert_eq!(listed.into_iter().map(|(_, order)| order["user"].clone()).collect::<Vec<_>>(), [json!("Alice")]);
+
+        let spec: AggregationSpec = serde_json::from_value(json!({"aggregates": [{"function": "sum", "field": "total", "as": "total"}]})).unwrap();
+        assert_eq!(snapshot.aggregate("orders", &spec).unwrap().groups[0].values["total"], json!(10));
+        assert_eq!(manager.aggregate("orders", &spec).unwrap().groups[0].values["total"], json!(42));
+
+        assert!(snapshot.release());
+        assert_eq!(manager.count("users").unwrap(), 2);
+    }
+
+    #[test]
+    fn test_compaction_keeps_pinned_history() {
+        let manager = manager_without_retention();
+        let id = manager.insert_value("users", json!({"name": "Alice"})).unwrap();
+        let gone = manager.insert_value("users", json!({"name": "Bob"})).unwrap();
+
+        let snapshot = manager.begin_read_snapshot().unwrap();
+        manager.update_value("users", &id, json!({"name": "Alicia"})).unwrap();
+        manager.delete("users", &gone).unwrap();
+        let kept = manager.compact(&DeadSpaceStrategy::default()).unwrap();
+        assert_eq!((kept.versions_dropped, kept.tombstones_dropped), (0, 0));
+        assert_eq!(snapshot.get_value("users", &id).unwrap(), Some(json!({"name": "Alice"})));
+        assert_eq!(snapshot.get_value("users", &gone).unwrap(), Some(json!({"name": "Bob"})));
+
+        // Released, the snapshot no longer holds the history back
+        let timestamp = snapshot.timestamp();
+        assert!(snapshot.release());
+        let report = manager.compact(&DeadSpaceStrategy::default()).unwrap();
+        assert_eq!(report.tombstones_dropped, 1);
+        assert!(report.versions_dropped >= 2);
+        assert!(matches!(manager.get_value_as_of("users", &id, timestamp), Err(DocumentError::HistoryUnavailable { .. })));
+    }
+
+    #[test]
+    fn test_snapshot_held_past_max_age_is_released() {
+        let manager = manager_without_retention().with_snapshot_max_age(Duration::ZERO);
+        let id = manager.insert_value("users", json!({"name": "Alice"})).unwrap();
+
+        let snapshot = manager.begin_read_snapshot().unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        manager.update_value("users", &id, json!({"name": "Alicia"})).unwrap();
+
+        assert!(!snapshot.is_active());
+        assert!(matches!(snapshot.get_value("users", &id), Err(DocumentError::SnapshotReleased { .. })));
+        assert!(!snapshot.release());
+    }
+}
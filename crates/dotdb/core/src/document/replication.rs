@@ -0,0 +1,933 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Read replicas through a change feed of document mutations
+//!
+//! On the primary, [`ChangeFeedStorage`] wraps the document storage and appends
+//! every successful write to a [`ChangeFeed`] under a sequence number, so the
+//! feed order is the order writes were applied. A [`Replica`] bootstraps from a
+//! snapshot taken at some sequence, then applies the records after it in order;
+//! its [`CollectionManager`] serves reads and rejects writes with
+//! [`DocumentError::ReadOnlyReplica`] naming the primary.
+//!
+//! The feed keeps a bounded number of records in memory. A replica that falls
+//! further behind than that gets [`DocumentError::ChangeFeedTruncated`] and has
+//! to bootstrap from a new snapshot. Sequences restart at 1 with the primary
+//! process, so replicas of a restarted primary bootstrap again as well.
+
+use super::compression::{CompressionCodec, RecompressionReport};
+use super::history::SnapshotPin;
+use super::snapshot::{SnapshotSummary, clear_storage, export_snapshot, import_snapshot, now_ms, put_document};
+use super::storage::{CoveringIndex, DocumentCompaction};
+use super::{CollectionManager, CollectionName, Document, DocumentError, DocumentId, DocumentResult, DocumentStorage, DocumentWrite, Namespace};
+use crate::compaction::strategy::DeadSpaceStrategy;
+use crate::statistics::CollectionSpaceUsage;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, RwLock};
+use std::time::{Duration, Instant};
+
+/// Records a [`ChangeFeed`] keeps by default
+pub const DEFAULT_FEED_RETENTION: usize = 100_000;
+
+/// A document mutation, as applied to the namespace of its [`ChangeRecord`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ChangeOp {
+    /// The document was created or its content replaced
+    PutDocument {
+        collection: CollectionName,
+        id: DocumentId,
+        content: Value,
+    },
+    DeleteDocument {
+        collection: CollectionName,
+        id: DocumentId,
+    },
+    CreateCollection {
+        collection: CollectionName,
+    },
+    DeleteCollection {
+        collection: CollectionName,
+    },
+    /// The collection was renamed with everything it holds
+    RenameCollection {
+        from: CollectionName,
+        to: CollectionName,
+    },
+    SetSchema {
+        collection: CollectionName,
+        schema: Option<Value>,
+    },
+    SetCompression {
+        collection: CollectionName,
+        codec: CompressionCodec,
+    },
+    CreateCoveringIndex {
+        collection: CollectionName,
+        paths: Vec<String>,
+    },
+    DropCoveringIndex {
+        collection: CollectionName,
+        paths: Vec<String>,
+    },
+    CreateNamespace {
+        namespace: String,
+    },
+    /// The namespace was dropped with all its collections
+    DropNamespace {
+        namespace: String,
+    },
+}
+
+/// A mutation committed on the primary
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChangeRecord {
+    /// Position in the feed, starting at 1 and without gaps
+    pub sequence: u64,
+    /// When the primary applied the mutation, in milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    /// Namespace the mutation was made in
+    pub namespace: String,
+    pub op: ChangeOp,
+}
+
+/// How far a replica trails the primary
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReplicationLag {
+    /// Records the primary has that the replica has not applied
+    pub records: u64,
+    /// Age of the oldest record the replica has not applied, zero when caught up
+    pub seconds: f64,
+}
+
+#[derive(Debug, Default)]
+struct FeedState {
+    records: VecDeque<ChangeRecord>,
+    last_sequence: u64,
+}
+
+/// Bounded, in-memory log of the mutations made through a [`ChangeFeedStorage`]
+#[derive(Debug)]
+pub struct ChangeFeed {
+    state: Mutex<FeedState>,
+    appended: Condvar,
+    retention: usize,
+}
+
+impl ChangeFeed {
+    /// Feed keeping the latest `retention` records
+    pub fn new(retention: usize) -> Self {
+        Self {
+            state: Mutex::new(FeedState::default()),
+            appended: Condvar::new(),
+            retention: retention.max(1),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, FeedState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Sequence of the latest record, 0 before the first write
+    pub fn last_sequence(&self) -> u64 {
+        self.lock().last_sequence
+    }
+
+    /// Up to `max` records after sequence `after`, in order
+    ///
+    /// Fails with [`DocumentError::ChangeFeedTruncated`] when records after `after`
+    /// have already been dropped.
+    pub fn read_after(&self, after: u64, max: usize) -> DocumentResult<Vec<ChangeRecord>> {
+        let state = self.lock();
+        if after >= state.last_sequence {
+            return Ok(Vec::new());
+        }
+        let oldest = state.records.front().map_or(state.last_sequence + 1, |record| record.sequence);
+        if after + 1 < oldest {
+            return Err(DocumentError::ChangeFeedTruncated { requested: after + 1, oldest });
+        }
+        let skip = (after + 1 - oldest) as usize;
+        Ok(state.records.iter().skip(skip).take(max).cloned().collect())
+    }
+
+    /// Wait up to `timeout` for a record after sequence `after`; returns whether there is one
+    pub fn wait_after(&self, after: u64, timeout: Duration) -> bool {
+        let state = self.lock();
+        let (state, _) = self
+            .appended
+            .wait_timeout_while(state, timeout, |state| state.last_sequence <= after)
+            .unwrap_or_else(|e| e.into_inner());
+        state.last_sequence > after
+    }
+
+    /// Lag of a replica that has applied every record up to `applied`
+    pub fn lag(&self, applied: u64) -> ReplicationLag {
+        let state = self.lock();
+        let records = state.last_sequence.saturating_sub(applied);
+        // The first record the replica lacks, or the oldest retained one if it was dropped
+        let pending = state.records.iter().find(|record| record.sequence > applied);
+        let seconds = match pending {
+            Some(record) if records > 0 => now_ms().saturating_sub(record.timestamp_ms) as f64 / 1000.0,
+            _ => 0.0,
+        };
+        ReplicationLag { records, seconds }
+    }
+
+    /// Apply `write` and, if it succeeds, append the mutations `ops` derives from its result
+    ///
+    /// Holding the feed lock across both keeps feed order equal to apply order.
+    fn record<T, I>(&self, namespace: &Namespace, write: impl FnOnce() -> DocumentResult<T>, ops: impl FnOnce(&T) -> I) -> DocumentResult<T>
+    where
+        I: IntoIterator<Item = ChangeOp>,
+    {
+        let mut state = self.lock();
+        let result = write()?;
+        for op in ops(&result) {
+            state.last_sequence += 1;
+            let record = ChangeRecord {
+                sequence: state.last_sequence,
+                timestamp_ms: now_ms(),
+                namespace: namespace.to_string(),
+                op,
+            };
+            state.records.push_back(record);
+            while state.records.len() > self.retention {
+                state.records.pop_front();
+            }
+            self.appended.notify_all();
+        }
+        Ok(result)
+    }
+}
+
+impl Default for ChangeFeed {
+    fn default() -> Self {
+        Self::new(DEFAULT_FEED_RETENTION)
+    }
+}
+
+/// Document storage of a primary, appending every mutation to a [`ChangeFeed`]
+pub struct ChangeFeedStorage {
+    inner: Arc<dyn DocumentStorage>,
+    feed: Arc<ChangeFeed>,
+}
+
+impl ChangeFeedStorage {
+    pub fn new(inner: Arc<dyn DocumentStorage>, feed: Arc<ChangeFeed>) -> Self {
+        Self { inner, feed }
+    }
+
+    pub fn feed(&self) -> &Arc<ChangeFeed> {
+        &self.feed
+    }
+
+    /// Export a snapshot consistent with the feed's current sequence
+    ///
+    /// Writes wait until the export is done.
+    pub fn export_snapshot(&self, out: &mut dyn Write) -> DocumentResult<SnapshotSummary> {
+        let state = self.feed.lock();
+        export_snapshot(self.inner.as_ref(), state.last_sequence, out)
+    }
+
+    fn record<T, I>(&self, write: impl FnOnce() -> DocumentResult<T>, ops: impl FnOnce(&T) -> I) -> DocumentResult<T>
+    where
+        I: IntoIterator<Item = ChangeOp>,
+    {
+        self.feed.record(self.inner.namespace(), write, ops)
+    }
+}
+
+impl DocumentStorage for ChangeFeedStorage {
+    fn create_document(&self, collection: &CollectionName, document: Document) -> DocumentResult<DocumentId> {
+        let content = document.content.clone();
+        self.record(
+            || self.inner.create_document(collection, document),
+            |id| {
+                Some(ChangeOp::PutDocument {
+                    collection: collection.clone(),
+                    id: id.clone(),
+                    content,
+                })
+            },
+        )
+    }
+
+    fn get_document(&self, collection: &CollectionName, id: &DocumentId) -> DocumentResult<Option<Document>> {
+        self.inner.get_document(collection, id)
+    }
+
+    fn get_document_for_scan(&self, collection: &CollectionName, id: &DocumentId) -> DocumentResult<Option<Document>> {
+        self.inner.get_document_for_scan(collection, id)
+    }
+
+    fn update_document(&self, collection: &CollectionName, document: Document) -> DocumentResult<()> {
+        let op = ChangeOp::PutDocument {
+            collection: collection.clone(),
+            id: document.id.clone(),
+            content: document.content.clone(),
+        };
+        self.record(|| self.inner.update_document(collection, document), |_| Some(op))
+    }
+
+    fn update_document_if_version(&self, collection: &CollectionName, document: Document, expected_version: u64) -> DocumentResult<u64> {
+        let op = ChangeOp::PutDocument {
+            collection: collection.clone(),
+            id: document.id.clone(),
+            content: document.content.clone(),
+        };
+        self.record(|| self.inner.update_document_if_version(collection, document, expected_version), |_| Some(op))
+    }
+
+    fn delete_document(&self, collection: &CollectionName, id: &DocumentId) -> DocumentResult<bool> {
+        self.record(
+            || self.inner.delete_document(collection, id),
+            |existed| {
+                existed.then(|| ChangeOp::DeleteDocument {
+                    collection: collection.clone(),
+                    id: id.clone(),
+                })
+            },
+        )
+    }
+
+    fn delete_document_if_version(&self, collection: &CollectionName, id: &DocumentId, expected_version: u64) -> DocumentResult<bool> {
+        self.record(
+            || self.inner.delete_document_if_version(collection, id, expected_version),
+            |existed| {
+                existed.then(|| ChangeOp::DeleteDocument {
+                    collection: collection.clone(),
+                    id: id.clone(),
+                })
+            },
+        )
+    }
+
+    fn document_exists(&self, collection: &CollectionName, id: &DocumentId) -> DocumentResult<bool> {
+        self.inner.document_exists(collection, id)
+    }
+
+    fn list_documents(&self, collection: &CollectionName) -> DocumentResult<Vec<DocumentId>> {
+        self.inner.list_documents(collection)
+    }
+
+    fn count_documents(&self, collection: &CollectionName) -> DocumentResult<usize> {
+        self.inner.count_documents(collection)
+    }
+
+    fn create_collection(&self, collection: &CollectionName) -> DocumentResult<()> {
+        self.record(|| self.inner.create_collection(collection), |_| Some(ChangeOp::CreateCollection { collection: collection.clone() }))
+    }
+
+    fn delete_collection(&self, collection: &CollectionName) -> DocumentResult<bool> {
+        self.record(
+            || self.inner.delete_collection(collection),
+            |existed| existed.then(|| ChangeOp::DeleteCollection { collection: collection.clone() }),
+        )
+    }
+
+    fn rename_collection(&self, from: &CollectionName, to: &CollectionName) -> DocumentResult<()> {
+        self.record(|| self.inner.rename_collection(from, to), |_| Some(ChangeOp::RenameCollection { from: from.clone(), to: to.clone() }))
+    }
+
+    fn list_collections(&self) -> DocumentResult<Vec<CollectionName>> {
+        self.inner.list_collections()
+    }
+
+    fn collection_exists(&self, collection: &CollectionName) -> DocumentResult<bool> {
+        self.inner.collection_exists(collection)
+    }
+
+    fn set_schema(&self, collection: &CollectionName, schema: Option<&Value>) -> DocumentResult<()> {
+        self.record(
+            || self.inner.set_schema(collection, schema),
+            |_| {
+                Some(ChangeOp::SetSchema {
+                    collection: collection.clone(),
+                    schema: schema.cloned(),
+                })
+            },
+        )
+    }
+
+    fn get_schema(&self, collection: &CollectionName) -> DocumentResult<Option<Value>> {
+        self.inner.get_schema(collection)
+    }
+
+    fn set_compression(&self, collection: &CollectionName, codec: CompressionCodec) -> DocumentResult<()> {
+        self.record(
+            || self.inner.set_compression(collection, codec),
+            |_| {
+                Some(ChangeOp::SetCompression {
+                    collection: collection.clone(),
+                    codec,
+                })
+            },
+        )
+    }
+
+    fn get_compression(&self, collection: &CollectionName) -> DocumentResult<CompressionCodec> {
+        self.inner.get_compression(collection)
+    }
+
+    // Rewrites leave contents unchanged, so replicas have nothing to apply
+    fn rewrite_documents(&self, collection: &CollectionName, ids: &[DocumentId]) -> DocumentResult<RecompressionReport> {
+        self.inner.rewrite_documents(collection, ids)
+    }
+
+    fn create_covering_index(&self, collection: &CollectionName, paths: &[String]) -> DocumentResult<bool> {
+        self.record(
+            || self.inner.create_covering_index(collection, paths),
+            |created| {
+                created.then(|| ChangeOp::CreateCoveringIndex {
+                    collection: collection.clone(),
+                    paths: paths.to_vec(),
+                })
+            },
+        )
+    }
+
+    fn drop_covering_index(&self, collection: &CollectionName, paths: &[String]) -> DocumentResult<bool> {
+        self.record(
+            || self.inner.drop_covering_index(collection, paths),
+            |dropped| {
+                dropped.then(|| ChangeOp::DropCoveringIndex {
+                    collection: collection.clone(),
+                    paths: paths.to_vec(),
+                })
+            },
+        )
+    }
+
+    fn covering_indexes(&self, collection: &CollectionName) -> DocumentResult<Vec<Vec<String>>> {
+        self.inner.covering_indexes(collection)
+    }
+
+    fn read_covering_index(&self, collection: &CollectionName, paths: &[String]) -> DocumentResult<Option<CoveringIndex>> {
+        self.inner.read_covering_index(collection, paths)
+    }
+
+    fn namespace(&self) -> &Namespace {
+        self.inner.namespace()
+    }
+
+    fn in_namespace(&self, namespace: &Namespace) -> Arc<dyn DocumentStorage> {
+        Arc::new(Self::new(self.inner.in_namespace(namespace), self.feed.clone()))
+    }
+
+    fn create_namespace(&self, namespace: &Namespace) -> DocumentResult<bool> {
+        self.record(
+            || self.inner.create_namespace(namespace),
+            |created| created.then(|| ChangeOp::CreateNamespace { namespace: namespace.to_string() }),
+        )
+    }
+
+    fn list_namespaces(&self) -> DocumentResult<Vec<Namespace>> {
+        self.inner.list_namespaces()
+    }
+
+    fn drop_namespace(&self, namespace: &Namespace, force: bool) -> DocumentResult<bool> {
+        self.record(
+            || self.inner.drop_namespace(namespace, force),
+            |dropped| dropped.then(|| ChangeOp::DropNamespace { namespace: namespace.to_string() }),
+        )
+    }
+
+    fn commit_writes(&self, writes: Vec<DocumentWrite>) -> DocumentResult<()> {
+        let ops: Vec<_> = writes
+            .iter()
+            .map(|write| match &write.content {
+                Some(content) => ChangeOp::PutDocument {
+                    collection: write.collection.clone(),
+                    id: write.id.clone(),
+                    content: content.clone(),
+                },
+                None => ChangeOp::DeleteDocument {
+                    collection: write.collection.clone(),
+                    id: write.id.clone(),
+                },
+            })
+            .collect();
+        self.record(|| self.inner.commit_writes(writes), |_| ops)
+    }
+
+    fn read_snapshot(&self, as_of: u64) -> DocumentResult<u64> {
+        self.inner.read_snapshot(as_of)
+    }
+
+    fn pin_snapshot(&self, max_age: Duration) -> DocumentResult<SnapshotPin> {
+        self.inner.pin_snapshot(max_age)
+    }
+
+    fn get_document_as_of(&self, collection: &CollectionName, id: &DocumentId, snapshot: u64) -> DocumentResult<Option<Document>> {
+        self.inner.get_document_as_of(collection, id, snapshot)
+    }
+
+    fn list_documents_as_of(&self, collection: &CollectionName, snapshot: u64) -> DocumentResult<Vec<DocumentId>> {
+        self.inner.list_documents_as_of(collection, snapshot)
+    }
+
+    fn space_usage(&self, collection: &CollectionName) -> DocumentResult<CollectionSpaceUsage> {
+        self.inner.space_usage(collection)
+    }
+
+    fn compact(&self, strategy: &DeadSpaceStrategy) -> DocumentResult<DocumentCompaction> {
+        self.inner.compact(strategy)
+    }
+
+    fn flush(&self) -> DocumentResult<()> {
+        self.inner.flush()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Applied {
+    sequence: u64,
+    /// Primary timestamp of the latest applied record
+    timestamp_ms: u64,
+}
+
+#[derive(Debug)]
+struct ReplicaProgress {
+    applied: Mutex<Applied>,
+    advanced: Condvar,
+    primary: RwLock<String>,
+}
+
+impl ReplicaProgress {
+    fn lock(&self) -> MutexGuard<'_, Applied> {
+        self.applied.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn read_only(&self) -> DocumentError {
+        DocumentError::ReadOnlyReplica {
+            primary: self.primary.read().unwrap_or_else(|e| e.into_inner()).clone(),
+        }
+    }
+}
+
+/// Local copy of a primary's documents, kept current by applying its change records
+///
+/// The applied sequence lives in memory, so a restarted replica bootstraps from a
+/// new snapshot before streaming again.
+pub struct Replica {
+    storage: Arc<dyn DocumentStorage>,
+    progress: Arc<ReplicaProgress>,
+}
+
+impl Replica {
+    /// Replica of the primary at `primary_address`, holding its copy in `storage`
+    pub fn new(storage: Arc<dyn DocumentStorage>, primary_address: impl Into<String>) -> Self {
+        Self {
+            storage,
+            progress: Arc::new(ReplicaProgress {
+                applied: Mutex::new(Applied::default()),
+                advanced: Condvar::new(),
+                primary: RwLock::new(primary_address.into()),
+            }),
+        }
+    }
+
+    /// Address of the primary writes are redirected to
+    pub fn primary_address(&self) -> String {
+        self.progress.primary.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn set_primary_address(&self, address: impl Into<String>) {
+        *self.progress.primary.write().unwrap_or_else(|e| e.into_inner()) = address.into();
+    }
+
+    /// Collection manager serving reads from the replica; its writes fail with [`DocumentError::ReadOnlyReplica`]
+    pub fn collection_manager(&self) -> CollectionManager {
+        CollectionManager::new(Arc::new(ReadOnlyStorage {
+            inner: self.storage.clone(),
+            progress: self.progress.clone(),
+        }))
+    }
+
+    /// Sequence of the latest applied record
+    pub fn applied_sequence(&self) -> u64 {
+        self.progress.lock().sequence
+    }
+
+    /// Primary timestamp of the latest applied record, in milliseconds since the Unix epoch
+    pub fn applied_timestamp_ms(&self) -> u64 {
+        self.progress.lock().timestamp_ms
+    }
+
+    /// Replace the replica's data with a snapshot and resume from its sequence
+    pub fn bootstrap(&self, snapshot: impl BufRead) -> DocumentResult<SnapshotSummary> {
+        let mut applied = self.progress.lock();
+        clear_storage(self.storage.as_ref())?;
+        let summary = import_snapshot(self.storage.as_ref(), snapshot)?;
+        *applied = Applied {
+            sequence: summary.sequence,
+            timestamp_ms: summary.taken_at_ms,
+        };
+        self.progress.advanced.notify_all();
+        Ok(summary)
+    }
+
+    /// Apply the next change record; returns `false` for a record already applied
+    ///
+    /// Fails with [`DocumentError::ReplicationGap`] if records between the applied
+    /// sequence and `record` are missing.
+    pub fn apply(&self, record: &ChangeRecord) -> DocumentResult<bool> {
+        let mut applied = self.progress.lock();
+        if record.sequence <= applied.sequence {
+            return Ok(false);
+        }
+        if record.sequence != applied.sequence + 1 {
+            return Err(DocumentError::ReplicationGap {
+                expected: applied.sequence + 1,
+                received: record.sequence,
+            });
+        }
+
+        let storage = self.storage.in_namespace(&Namespace::new(record.namespace.as_str())?);
+        match &record.op {
+            ChangeOp::PutDocument { collection, id, content } => put_document(storage.as_ref(), collection, id.clone(), content.clone())?,
+            ChangeOp::DeleteDocument { collection, id } => {
+                storage.delete_document(collection, id)?;
+            }
+            ChangeOp::CreateCollection { collection } => storage.create_collection(collection)?,
+            ChangeOp::DeleteCollection { collection } => {
+                storage.delete_collection(collection)?;
+            }
+            ChangeOp::RenameCollection { from, to } => storage.rename_collection(from, to)?,
+            ChangeOp::SetSchema { collection, schema } => storage.set_schema(collection, schema.as_ref())?,
+            ChangeOp::SetCompression { collection, codec } => storage.set_compression(collection, *codec)?,
+            ChangeOp::CreateCoveringIndex { collection, paths } => {
+                storage.create_covering_index(collection, paths)?;
+            }
+            ChangeOp::DropCoveringIndex { collection, paths } => {
+                storage.drop_covering_index(collection, paths)?;
+            }
+            ChangeOp::CreateNamespace { namespace } => {
+                storage.create_namespace(&Namespace::new(namespace.as_str())?)?;
+            }
+            ChangeOp::DropNamespace { namespace } => {
+                storage.drop_namespace(&Namespace::new(namespace.as_str())?, true)?;
+            }
+        }
+
+        *applied = Applied {
+            sequence: record.sequence,
+            timestamp_ms: record.timestamp_ms,
+        };
+        self.progress.advanced.notify_all();
+        Ok(true)
+    }
+
+    /// Wait up to `timeout` until the replica has applied `sequence`, for reads that
+    /// must be at least as fresh as a write acknowledged at that sequence
+    pub fn wait_for_sequence(&self, sequence: u64, timeout: Duration) -> DocumentResult<()> {
+        let deadline = Instant::now() + timeout;
+        let mut applied = self.progress.lock();
+        while applied.sequence < sequence {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(DocumentError::ReplicaBehind {
+                    applied: applied.sequence,
+                    required: sequence,
+                });
+            }
+            applied = self.progress.advanced.wait_timeout(applied, remaining).unwrap_or_else(|e| e.into_inner()).0;
+        }
+        Ok(())
+    }
+}
+
+/// Storage handed to a replica's readers: reads pass through, writes are refused
+struct ReadOnlyStorage {
+    inner: Arc<dyn DocumentStorage>,
+    progress: Arc<ReplicaProgress>,
+}
+
+impl DocumentStorage for ReadOnlyStorage {
+    fn create_document(&self, _collection: &CollectionName, _document: Document) -> DocumentResult<DocumentId> {
+        Err(self.progress.read_only())
+    }
+
+    fn get_document(&self, collection: &CollectionName, id: &DocumentId) -> DocumentResult<Option<Document>> {
+        self.inner.get_document(collection, id)
+    }
+
+    fn get_document_for_scan(&self, collection: &CollectionName, id: &DocumentId) -> DocumentResult<Option<Document>> {
+        self.inner.get_document_for_scan(collection, id)
+    }
+
+    fn update_document(&self, _collection: &CollectionName, _document: Document) -> DocumentResult<()> {
+        Err(self.progress.read_only())
+    }
+
+    fn delete_document(&self, _collection: &CollectionName, _id: &DocumentId) -> DocumentResult<bool> {
+        Err(self.progress.read_only())
+    }
+
+    fn update_document_if_version(&self, _collection: &CollectionName, _document: Document, _expected_version: u64) -> DocumentResult<u64> {
+        Err(self.progress.read_only())
+    }
+
+    fn delete_document_if_version(&self, _collection: &CollectionName, _id: &DocumentId, _expected_version: u64) -> DocumentResult<bool> {
+        Err(self.progress.read_only())
+    }
+
+    fn document_exists(&self, collection: &CollectionName, id: &DocumentId) -> DocumentResult<bool> {
+        self.inner.document_exists(collection, id)
+    }
+
+    fn list_documents(&self, collection: &CollectionName) -> DocumentResult<Vec<DocumentId>> {
+        self.inner.list_documents(collection)
+    }
+
+    fn count_documents(&self, collection: &CollectionName) -> DocumentResult<usize> {
+        self.inner.count_documents(collection)
+    }
+
+    fn create_collection(&self, _collection: &CollectionName) -> DocumentResult<()> {
+        Err(self.progress.read_only())
+    }
+
+    fn delete_collection(&self, _collection: &CollectionName) -> DocumentResult<bool> {
+        Err(self.progress.read_only())
+    }
+
+    fn rename_collection(&self, _from: &CollectionName, _to: &CollectionName) -> DocumentResult<()> {
+        Err(self.progress.read_only())
+    }
+
+    fn list_collections(&self) -> DocumentResult<Vec<CollectionName>> {
+        self.inner.list_collections()
+    }
+
+    fn collection_exists(&self, collection: &CollectionName) -> DocumentResult<bool> {
+        self.inner.collection_exists(collection)
+    }
+
+    fn set_schema(&self, _collection: &CollectionName, _schema: Option<&Value>) -> DocumentResult<()> {
+        Err(self.progress.read_only())
+    }
+
+    fn get_schema(&self, collection: &CollectionName) -> DocumentResult<Option<Value>> {
+        self.inner.get_schema(collection)
+    }
+
+    fn set_compression(&self, _collection: &CollectionName, _codec: CompressionCodec) -> DocumentResult<()> {
+        Err(self.progress.read_only())
+    }
+
+    fn get_compression(&self, collection: &CollectionName) -> DocumentResult<CompressionCodec> {
+        self.inner.get_compression(collection)
+    }
+
+    fn rewrite_documents(&self, _collection: &CollectionName, _ids: &[DocumentId]) -> DocumentResult<RecompressionReport> {
+        Err(self.progress.read_only())
+    }
+
+    fn create_covering_index(&self, _collection: &CollectionName, _paths: &[String]) -> DocumentResult<bool> {
+        Err(self.progress.read_only())
+    }
+
+    fn drop_covering_index(&self, _collection: &CollectionName, _paths: &[String]) -> DocumentResult<bool> {
+        Err(self.progress.read_only())
+    }
+
+    fn covering_indexes(&self, collection: &CollectionName) -> DocumentResult<Vec<Vec<String>>> {
+        self.inner.covering_indexes(collection)
+    }
+
+    fn read_covering_index(&self, collection: &CollectionName, paths: &[String]) -> DocumentResult<Option<CoveringIndex>> {
+        self.inner.read_covering_index(collection, paths)
+    }
+
+    fn namespace(&self) -> &Namespace {
+        self.inner.namespace()
+    }
+
+    fn in_namespace(&self, namespace: &Namespace) -> Arc<dyn DocumentStorage> {
+        Arc::new(Self {
+            inner: self.inner.in_namespace(namespace),
+            progress: self.progress.clone(),
+        })
+    }
+
+    fn create_namespace(&self, _namespace: &Namespace) -> DocumentResult<bool> {
+        Err(self.progress.read_only())
+    }
+
+    fn list_namespaces(&self) -> DocumentResult<Vec<Namespace>> {
+        self.inner.list_namespaces()
+    }
+
+    fn drop_namespace(&self, _namespace: &Namespace, _force: bool) -> DocumentResult<bool> {
+        Err(self.progress.read_only())
+    }
+
+    fn commit_writes(&self, _writes: Vec<DocumentWrite>) -> DocumentResult<()> {
+        Err(self.progress.read_only())
+    }
+
+    fn read_snapshot(&self, as_of: u64) -> DocumentResult<u64> {
+        self.inner.read_snapshot(as_of)
+    }
+
+    fn pin_snapshot(&self, max_age: Duration) -> DocumentResult<SnapshotPin> {
+        self.inner.pin_snapshot(max_age)
+    }
+
+    fn get_document_as_of(&self, collection: &CollectionName, id: &DocumentId, snapshot: u64) -> DocumentResult<Option<Document>> {
+        self.inner.get_document_as_of(collection, id, snapshot)
+    }
+
+    fn list_documents_as_of(&self, collection: &CollectionName, snapshot: u64) -> DocumentResult<Vec<DocumentId>> {
+        self.inner.list_documents_as_of(collection, snapshot)
+    }
+
+    fn space_usage(&self, collection: &CollectionName) -> DocumentResult<CollectionSpaceUsage> {
+        self.inner.space_usage(collection)
+    }
+
+    fn compact(&self, strategy: &DeadSpaceStrategy) -> DocumentResult<DocumentCompaction> {
+        self.inner.compact(strategy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::DocumentStore;
+    use crate::state::db_interface::Database;
+    use serde_json::json;
+
+    fn store() -> Arc<dyn DocumentStorage> {
+        Arc::new(DocumentStore::new(Arc::new(Database::new_in_memory().unwrap())))
+    }
+
+    fn primary(retention: usize) -> (CollectionManager, Arc<ChangeFeedStorage>) {
+        let storage = Arc::new(ChangeFeedStorage::new(store(), Arc::new(ChangeFeed::new(retention))));
+        (CollectionManager::new(storage.clone()), storage)
+    }
+
+    fn catch_up(replica: &Replica, feed: &ChangeFeed) -> DocumentResult<()> {
+        for record in feed.read_after(replica.applied_sequence(), usize::MAX)? {
+            replica.apply(&record)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_replica_follows_snapshot_and_feed() {
+        let (manager, storage) = primary(DEFAULT_FEED_RETENTION);
+        let alice = manager.insert_value("users", json!({"name": "Alice"})).unwrap();
+        let bob = manager.insert_value("users", json!({"name": "Bob"})).unwrap();
+        manager.set_compression("users", CompressionCodec::Lz4).unwrap();
+
+        let mut snapshot = Vec::new();
+        let summary = storage.export_snapshot(&mut snapshot).unwrap();
+        assert_eq!(summary.sequence, storage.feed().last_sequence());
+
+        let replica = Replica::new(store(), "primary:50051");
+        replica.bootstrap(snapshot.as_slice()).unwrap();
+        let reader = replica.collection_manager();
+        assert_eq!(reader.count("users").unwrap(), 2);
+        assert_eq!(reader.get_compression("users").unwrap(), CompressionCodec::Lz4);
+
+        manager.update_value("users", &alice, json!({"name": "Alice", "admin": true})).unwrap();
+        manager.delete("users", &bob).unwrap();
+        let tenant = manager.with_namespace("tenant-a").unwrap();
+        tenant.insert_value("orders", json!({"total": 7})).unwrap();
+        tenant.set_compression("orders", CompressionCodec::Zstd { level: 9 }).unwrap();
+        assert_eq!(replica.applied_sequence(), summary.sequence);
+        assert_eq!(storage.feed().lag(replica.applied_sequence()).records, storage.feed().last_sequence() - summary.sequence);
+
+        catch_up(&replica, storage.feed()).unwrap();
+        assert_eq!(reader.get_value("users", &alice).unwrap(), Some(json!({"name": "Alice", "admin": true})));
+        assert!(!reader.exists("users", &bob).unwrap());
+        assert_eq!(reader.with_namespace("tenant-a").unwrap().count("orders").unwrap(), 1);
+        assert_eq!(reader.with_namespace("tenant-a").unwrap().get_compression("orders").unwrap(), CompressionCodec::Zstd { level: 9 });
+        assert_eq!(storage.feed().lag(replica.applied_sequence()), ReplicationLag { records: 0, seconds: 0.0 });
+
+        manager.rename_collection("users", "people").unwrap();
+        catch_up(&replica, storage.feed()).unwrap();
+        assert!(!reader.collection_exists("users").unwrap());
+        assert_eq!(reader.get_value("people", &alice).unwrap(), Some(json!({"name": "Alice", "admin": true})));
+        assert!(matches!(reader.rename_collection("people", "users"), Err(DocumentError::ReadOnlyReplica { .. })));
+
+        // Re-delivered records after a reconnect are skipped
+        let replayed = storage.feed().read_after(summary.sequence, 1).unwrap();
+        assert!(!replica.apply(&replayed[0]).unwrap());
+    }
+
+    #[test]
+    fn test_replica_rejects_writes_with_primary_address() {
+        let replica = Replica::new(store(), "10.0.0.1:50051");
+        let reader = replica.collection_manager();
+
+        let error = reader.insert_value("users", json!({"name": "Eve"})).unwrap_err();
+        assert!(matches!(&error, DocumentError::ReadOnlyReplica { primary } if primary == "10.0.0.1:50051"));
+        assert!(matches!(reader.create_namespace("tenant-b"), Err(DocumentError::ReadOnlyReplica { .. })));
+        assert!(matches!(reader.set_compression("users", CompressionCodec::Lz4), Err(DocumentError::ReadOnlyReplica { .. })));
+
+        let mut txn = reader.begin_transaction(crate::storage_engine::IsolationLevel::ReadCommitted);
+        txn.insert_value("users", json!({"name": "Eve"})).unwrap();
+        assert!(matches!(txn.commit(), Err(DocumentError::ReadOnlyReplica { .. })));
+    }
+
+    #[test]
+    fn test_gaps_and_truncation_are_reported() {
+        let (manager, storage) = primary(2);
+        for i in 0..4 {
+            manager.insert_value("events", json!({"n": i})).unwrap();
+        }
+
+        // Creating the collection on first insert is part of the insert's record
+        assert_eq!(storage.feed().last_sequence(), 4);
+        assert!(matches!(storage.feed().read_after(0, 10), Err(DocumentError::ChangeFeedTruncated { requested: 1, oldest: 3 })));
+        let records = storage.feed().read_after(2, 10).unwrap();
+        assert_eq!(records.len(), 2);
+
+        let replica = Replica::new(store(), "primary:50051");
+        assert!(matches!(replica.apply(&records[0]), Err(DocumentError::ReplicationGap { expected: 1, received: 3 })));
+    }
+
+    #[test]
+    fn test_wait_for_sequence() {
+        let (manager, storage) = primary(DEFAULT_FEED_RETENTION);
+        let replica = Arc::new(Replica::new(store(), "primary:50051"));
+        manager.insert_value("users", json!({"name": "Alice"})).unwrap();
+        let target = storage.feed().last_sequence();
+
+        assert!(matches!(
+            replica.wait_for_sequence(target, Duration::from_millis(10)),
+            Err(DocumentError::ReplicaBehind { applied: 0, required }) if required == target
+        ));
+
+        let applier = {
+            let replica = replica.clone();
+            let feed = storage.feed().clone();
+            std::thread::spawn(move || {
+                assert!(feed.wait_after(0, Duration::from_secs(5)));
+                catch_up(&replica, &feed).unwrap();
+            })
+        };
+        replica.wait_for_sequence(target, Duration::from_secs(5)).unwrap();
+        applier.join().unwrap();
+        assert_eq!(replica.collection_manager().count("users").unwrap(), 1);
+    }
+}
@@ -0,0 +1,604 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Per-collection JSON Schema validation
+//!
+//! Schemas are a subset of JSON Schema draft 2020-12, compiled once and then
+//! checked against every document written to the collection. Supported
+//! keywords:
+//!
+//! - `type` (a name or a list of names), `enum`, `const`
+//! - `properties`, `required`, `additionalProperties`, `minProperties`, `maxProperties`
+//! - `items`, `minItems`, `maxItems`, `uniqueItems`
+//! - `minimum`, `maximum`, `exclusiveMinimum`, `exclusiveMaximum`, `multipleOf`
+//! - `minLength`, `maxLength`
+//! - `allOf`, `anyOf`, `oneOf`, `not`
+//! - `true` and `false` as schemas
+//!
+//! Annotations such as `title`, `description` and `format` are accepted and
+//! ignored. Any other keyword is rejected when the schema is compiled, so a
+//! schema never silently enforces less than it appears to.
+
+use super::{CollectionName, DocumentError, DocumentId, DocumentResult};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// Keywords that carry no assertion and are ignored
+const ANNOTATION_KEYWORDS: &[&str] = &[
+    "$schema",
+    "$id",
+    "$comment",
+    "title",
+    "description",
+    "default",
+    "examples",
+    "format",
+    "deprecated",
+    "readOnly",
+    "writeOnly",
+];
+
+/// Whether writes are checked against the collection's schema
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Validation {
+    #[default]
+    Enforce,
+    /// Skip the check, for trusted internal writers that already produce valid documents
+    Skip,
+}
+
+/// One way in which a document does not match a schema
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SchemaViolation {
+    /// JSON pointer to the offending value, empty for the document root
+    pub path: String,
+    /// What the schema requires at `path`
+    pub expected: String,
+    /// The value found at `path`, `None` when a required property is missing
+    pub actual: Option<Value>,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() { "/" } else { &self.path };
+        match &self.actual {
+            Some(actual) => write!(f, "{path}: expected {}, found {actual}", self.expected),
+            None => write!(f, "{path}: expected {}, found nothing", self.expected),
+        }
+    }
+}
+
+/// Summary of a violation list for error messages
+pub(crate) fn summarize(violations: &[SchemaViolation]) -> String {
+    match violations {
+        [] => "no violations".to_string(),
+        [only] => only.to_string(),
+        [first, rest @ ..] => format!("{first} (and {} more)", rest.len()),
+    }
+}
+
+/// Violations found in one stored document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentViolations {
+    pub id: DocumentId,
+    pub violations: Vec<SchemaViolation>,
+}
+
+/// Result of checking a collection's existing documents against its schema
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SchemaReport {
+    pub documents_checked: usize,
+    /// Documents that do not match, in collection order
+    pub invalid: Vec<DocumentViolations>,
+}
+
+impl SchemaReport {
+    pub fn is_valid(&self) -> bool {
+        self.invalid.is_empty()
+    }
+}
+
+/// JSON value types as named by the `type` keyword
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JsonType {
+    Null,
+    Boolean,
+    Object,
+    Array,
+    Number,
+    String,
+    Integer,
+}
+
+impl JsonType {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "null" => JsonType::Null,
+            "boolean" => JsonType::Boolean,
+            "object" => JsonType::Object,
+            "array" => JsonType::Array,
+            "number" => JsonType::Number,
+            "string" => JsonType::String,
+            "integer" => JsonType::Integer,
+            _ => return None,
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            JsonType::Null => "null",
+            JsonType::Boolean => "boolean",
+            JsonType::Object => "object",
+            JsonType::Array => "array",
+            JsonType::Number => "number",
+            JsonType::String => "string",
+            JsonType::Integer => "integer",
+        }
+    }
+
+    fn matches(self, value: &Value) -> bool {
+        match (self, value) {
+            (JsonType::Null, Value::Null) | (JsonType::Boolean, Value::Bool(_)) | (JsonType::Object, Value::Object(_)) | (JsonType::Array, Value::Array(_)) => true,
+            (JsonType::Number, Value::Number(_)) | (JsonType::String, Value::String(_)) => true,
+            // 2020-12 counts numbers with a zero fractional part, such as 1.0, as integers
+            (JsonType::Integer, Value::Number(n)) => n.is_i64() || n.is_u64() || n.as_f64().is_some_and(|f| f.fract() == 0.0),
+            _ => false,
+        }
+    }
+}
+
+/// A compiled schema or subschema
+#[derive(Debug, Clone)]
+enum Node {
+    /// `true` accepts everything, `false` nothing
+    Bool(bool),
+    Keywords(Box<Keywords>),
+}
+
+#[derive(Debug, Clone, Default)]
+struct Keywords {
+    types: Option<Vec<JsonType>>,
+    enum_values: Option<Vec<Value>>,
+    const_value: Option<Value>,
+    properties: Vec<(String, Node)>,
+    required: Vec<String>,
+    additional_properties: Option<Node>,
+    min_properties: Option<usize>,
+    max_properties: Option<usize>,
+    items: Option<Node>,
+    min_items: Option<usize>,
+    max_items: Option<usize>,
+    unique_items: bool,
+    minimum: Option<f64>,
+    maximum: Option<f64>,
+    exclusive_minimum: Option<f64>,
+    exclusive_maximum: Option<f64>,
+    multiple_of: Option<f64>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    all_of: Vec<Node>,
+    any_of: Vec<Node>,
+    one_of: Vec<Node>,
+    not: Option<Node>,
+}
+
+/// A compiled collection schema
+#[derive(Debug, Clone)]
+pub struct JsonSchema {
+    root: Node,
+}
+
+impl JsonSchema {
+    /// Compile `schema`, rejecting keywords outside the supported subset
+    pub fn compile(schema: &Value) -> DocumentResult<Self> {
+        Ok(Self { root: compile_node(schema, "")? })
+    }
+
+    /// Parse and compile a schema from a JSON string
+    pub fn from_json_str(json: &str) -> DocumentResult<Self> {
+        let schema: Value = serde_json::from_str(json)?;
+        Self::compile(&schema)
+    }
+
+    /// Every way in which `value` does not match the schema
+    pub fn validate(&self, value: &Value) -> Vec<SchemaViolation> {
+        let mut violations = Vec::new();
+        check(&self.root, value, &mut String::new(), &mut violations);
+        violations
+    }
+
+    pub fn is_valid(&self, value: &Value) -> bool {
+        self.validate(value).is_empty()
+    }
+
+    /// Fail with [`DocumentError::SchemaViolation`] unless `value` matches
+    pub fn check_document(&self, collection: &CollectionName, value: &Value) -> DocumentResult<()> {
+        let violations = self.validate(value);
+        if violations.is_empty() {
+            return Ok(());
+        }
+        Err(DocumentError::SchemaViolation {
+            collection: collection.clone(),
+            violations,
+        })
+    }
+}
+
+fn invalid(path: &str, message: impl fmt::Display) -> DocumentError {
+    let path = if path.is_empty() { "/" } else { path };
+    DocumentError::InvalidSchema(format!("{path}: {message}"))
+}
+
+fn compile_node(schema: &Value, path: &str) -> DocumentResult<Node> {
+    let object = match schema {
+        Value::Bool(accept) => return Ok(Node::Bool(*accept)),
+        Value::Object(object) => object,
+        other => return Err(invalid(path, format!("a schema must be an object or a boolean, found {other}"))),
+    };
+
+    let mut keywords = Keywords::default();
+    for (keyword, value) in object {
+        let at = format!("{path}/{}", escape_pointer(keyword));
+        match keyword.as_str() {
+            "type" => {
+                let names = match value {
+                    Value::String(name) => vec![name.as_str()],
+                    Value::Array(names) => names
+                        .iter()
+                        .map(|n| n.as_str().ok_or_else(|| invalid(&at, "type names must be strings")))
+                        .collect::<DocumentResult<_>>()?,
+                    _ => return Err(invalid(&at, "expected a type name or a list of type names")),
+                };
+                let types = names
+                    .into_iter()
+                    .map(|name| JsonType::parse(name).ok_or_else(|| invalid(&at, format!("unknown type '{name}'"))))
+                    .collect::<DocumentResult<_>>()?;
+                keywords.types = Some(types);
+            }
+            "enum" => keywords.enum_values = Some(value.as_array().ok_or_else(|| invalid(&at, "expected an array"))?.clone()),
+            "const" => keywords.const_value = Some(value.clone()),
+            "properties" => {
+                for (name, subschema) in value.as_object().ok_or_else(|| invalid(&at, "expected an object"))? {
+                    let node = compile_node(subschema, &format!("{at}/{}", escape_pointer(name)))?;
+                    keywords.properties.push((name.clone(), node));
+                }
+            }
+            "required" => {
+                let names = value.as_array().ok_or_else(|| invalid(&at, "expected an array of property names"))?;
+                keywords.required = names
+                    .iter()
+                    .map(|n| n.as_str().map(str::to_string).ok_or_else(|| invalid(&at, "property names must be strings")))
+                    .collect::<DocumentResult<_>>()?;
+            }
+            "additionalProperties" => keywords.additional_properties = Some(compile_node(value, &at)?),
+            "minProperties" => keywords.min_properties = Some(count(value, &at)?),
+            "maxProperties" => keywords.max_properties = Some(count(value, &at)?),
+            "items" => keywords.items = Some(compile_node(value, &at)?),
+            "minItems" => keywords.min_items = Some(count(value, &at)?),
+            "maxItems" => keywords.max_items = Some(count(value, &at)?),
+            "uniqueItems" => keywords.unique_items = value.as_bool().ok_or_else(|| invalid(&at, "expected a boolean"))?,
+            "minimum" => keywords.minimum = Some(number(value, &at)?),
+            "maximum" => keywords.maximum = Some(number(value, &at)?),
+            "exclusiveMinimum" => keywords.exclusive_minimum = Some(number(value, &at)?),
+            "exclusiveMaximum" => keywords.exclusive_maximum = Some(number(value, &at)?),
+            "multipleOf" => {
+                let divisor = number(value, &at)?;
+                if divisor <= 0.0 {
+                    return Err(invalid(&at, "expected a number greater than 0"));
+                }
+                keywords.multiple_of = Some(divisor);
+            }
+            "minLength" => keywords.min_length = Some(count(value, &at)?),
+            "maxLength" => keywords.max_length = Some(count(value, &at)?),
+            "allOf" => keywords.all_of = subschemas(value, &at)?,
+            "anyOf" => keywords.any_of = subschemas(value, &at)?,
+            "oneOf" => keywords.one_of = subschemas(value, &at)?,
+            "not" => keywords.not = Some(compile_node(value, &at)?),
+            keyword if ANNOTATION_KEYWORDS.contains(&keyword) => {}
+            keyword => return Err(invalid(path, format!("unsupported keyword '{keyword}'"))),
+        }
+    }
+
+    Ok(Node::Keywords(Box::new(keywords)))
+}
+
+fn count(value: &Value, path: &str) -> DocumentResult<usize> {
+    value.as_u64().map(|n| n as usize).ok_or_else(|| invalid(path, "expected a non-negative integer"))
+}
+
+fn number(value: &Value, path: &str) -> DocumentResult<f64> {
+    value.as_f64().ok_or_else(|| invalid(path, "expected a number"))
+}
+
+fn subschemas(value: &Value, path: &str) -> DocumentResult<Vec<Node>> {
+    let schemas = value.as_array().filter(|s| !s.is_empty()).ok_or_else(|| invalid(path, "expected a non-empty array of schemas"))?;
+    schemas.iter().enumerate().map(|(i, schema)| compile_node(schema, &format!("{path}/{i}"))).collect()
+}
+
+/// Escape a property name for use as a JSON pointer segment (RFC 6901)
+fn escape_pointer(segment: &str) -> String {
+    segment.replace('~', "~0").replace('/', "~1")
+}
+
+fn violation(violations: &mut Vec<SchemaViolation>, path: &str, expected: impl Into<String>, actual: Option<&Value>) {
+    violations.push(SchemaViolation {
+        path: path.to_string(),
+        expected: expected.into(),
+        actual: actual.cloned(),
+    });
+}
+
+fn matches(node: &Node, value: &Value) -> bool {
+    let mut violations = Vec::new();
+    check(node, value, &mut String::new(), &mut violations);
+    violations.is_empty()
+}
+
+fn check(node: &Node, value: &Value, path: &mut String, violations: &mut Vec<SchemaViolation>) {
+    let keywords = match node {
+        Node::Bool(true) => return,
+        Node::Bool(false) => return violation(violations, path, "nothing (schema is false)", Some(value)),
+        Node::Keywords(keywords) => keywords,
+    };
+
+    if let Some(types) = &keywords.types
+        && !types.iter().any(|t| t.matches(value))
+    {
+        let names: Vec<_> = types.iter().map(|t| t.name()).collect();
+        violation(violations, path, format!("type {}", names.join(" or ")), Some(value));
+        // Further keywords would only restate the type mismatch
+        return;
+    }
+    if let Some(values) = &keywords.enum_values
+        && !values.contains(value)
+    {
+        violation(violations, path, format!("one of {}", Value::Array(values.clone())), Some(value));
+    }
+    if let Some(expected) = &keywords.const_value
+        && expected != value
+    {
+        violation(violations, path, format!("the constant {expected}"), Some(value));
+    }
+
+    match value {
+        Value::Object(object) => check_object(keywords, object, path, violations),
+        Value::Array(items) => check_array(keywords, items, path, violations),
+        Value::Number(n) => {
+            if let Some(n) = n.as_f64() {
+                check_number(keywords, n, value, path, violations);
+            }
+        }
+        Value::String(s) => {
+            let length = s.chars().count();
+            if let Some(min) = keywords.min_length
+                && length < min
+            {
+                violation(violations, path, format!("at least {min} characters"), Some(value));
+            }
+            if let Some(max) = keywords.max_length
+                && length > max
+            {
+                violation(violations, path, format!("at most {max} characters"), Some(value));
+            }
+        }
+        Value::Null | Value::Bool(_) => {}
+    }
+
+    for subschema in &keywords.all_of {
+        check(subschema, value, path, violations);
+    }
+    if !keywords.any_of.is_empty() && !keywords.any_of.iter().any(|s| matches(s, value)) {
+        violation(violations, path, format!("a match for any of {} schemas", keywords.any_of.len()), Some(value));
+    }
+    if !keywords.one_of.is_empty() {
+        let matched = keywords.one_of.iter().filter(|s| matches(s, value)).count();
+        if matched != 1 {
+            violation(
+                violations,
+                path,
+                format!("a match for exactly one of {} schemas, matched {matched}", keywords.one_of.len()),
+                Some(value),
+            );
+        }
+    }
+    if let Some(not) = &keywords.not
+        && matches(not, value)
+    {
+        violation(violations, path, "no match for the `not` schema", Some(value));
+    }
+}
+
+fn check_object(keywords: &Keywords, object: &Map<String, Value>, path: &mut String, violations: &mut Vec<SchemaViolation>) {
+    for name in &keywords.required {
+        if !object.contains_key(name) {
+            violation(violations, &format!("{path}/{}", escape_pointer(name)), "required property", None);
+        }
+    }
+    if let Some(min) = keywords.min_properties
+        && object.len() < min
+    {
+        violation(violations, path, format!("at least {min} properties"), Some(&Value::Object(object.clone())));
+    }
+    if let Some(max) = keywords.max_properties
+        && object.len() > max
+    {
+        violation(violations, path, format!("at most {max} properties"), Some(&Value::Object(object.clone())));
+    }
+
+    for (name, value) in object {
+        let declared = keywords.properties.iter().find(|(property, _)| property == name).map(|(_, node)| node);
+        let Some(node) = declared.or(keywords.additional_properties.as_ref()) else { continue };
+
+        let len = path.len();
+        path.push('/');
+        path.push_str(&escape_pointer(name));
+        if declared.is_none() && matches!(node, Node::Bool(false)) {
+            violation(violations, path, "no additional properties", Some(value));
+        } else {
+            check(node, value, path, violations);
+        }
+        path.truncate(len);
+    }
+}
+
+fn check_array(keywords: &Keywords, items: &[Value], path: &mut String, violations: &mut Vec<SchemaViolation>) {
+    if let Some(min) = keywords.min_items
+        && items.len() < min
+    {
+        violation(violations, path, format!("at least {min} items"), Some(&Value::Array(items.to_vec())));
+    }
+    if let Some(max) = keywords.max_items
+        && items.len() > max
+    {
+        violation(violations, path, format!("at most {max} items"), Some(&Value::Array(items.to_vec())));
+    }
+    if keywords.unique_items {
+        for (i, item) in items.iter().enumerate() {
+            if items[..i].contains(item) {
+                violation(violations, &format!("{path}/{i}"), "an item not already in the array", Some(item));
+            }
+        }
+    }
+
+    if let Some(node) = &keywords.items {
+        for (i, item) in items.iter().enumerate() {
+            let len = path.len();
+            path.push_str(&format!("/{i}"));
+            check(node, item, path, violations);
+            path.truncate(len);
+        }
+    }
+}
+
+fn check_number(keywords: &Keywords, n: f64, value: &Value, path: &str, violations: &mut Vec<SchemaViolation>) {
+    if let Some(min) = keywords.minimum
+        && n < min
+    {
+        violation(violations, path, format!("a number >= {min}"), Some(value));
+    }
+    if let Some(max) = keywords.maximum
+        && n > max
+    {
+        violation(violations, path, format!("a number <= {max}"), Some(value));
+    }
+    if let Some(min) = keywords.exclusive_minimum
+        && n <= min
+    {
+        violation(violations, path, format!("a number > {min}"), Some(value));
+    }
+    if let Some(max) = keywords.exclusive_maximum
+        && n >= max
+    {
+        violation(violations, path, format!("a number < {max}"), Some(value));
+    }
+    if let Some(divisor) = keywords.multiple_of
+        && (n / divisor).fract() != 0.0
+    {
+        violation(violations, path, format!("a multiple of {divisor}"), Some(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn user_schema() -> JsonSchema {
+        JsonSchema::compile(&json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "type": "object",
+            "required": ["name", "age"],
+            "properties": {
+                "name": {"type": "string", "minLength": 1},
+                "age": {"type": "integer", "minimum": 0},
+                "tags": {"type": "array", "items": {"type": "string"}, "uniqueItems": true},
+                "role": {"enum": ["admin", "user"]}
+            },
+            "additionalProperties": false
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_valid_document() {
+        let schema = user_schema();
+        assert!(schema.is_valid(&json!({"name": "Ada", "age": 36, "tags": ["a", "b"], "role": "admin"})));
+        assert!(schema.is_valid(&json!({"name": "Ada", "age": 36.0})));
+    }
+
+    #[test]
+    fn test_violations_carry_pointer_expectation_and_value() {
+        let schema = user_schema();
+        let violations = schema.validate(&json!({"name": "", "tags": ["a", 7, "a"], "role": "root", "extra": true}));
+
+        let at = |path: &str| violations.iter().find(|v| v.path == path).unwrap_or_else(|| panic!("no violation at {path}: {violations:?}"));
+        assert_eq!(at("/age").actual, None);
+        assert_eq!(at("/age").expected, "required property");
+        assert_eq!(at("/name").actual, Some(json!("")));
+        assert_eq!(at("/tags/1").expected, "type string");
+        assert_eq!(at("/tags/2").actual, Some(json!("a")));
+        assert_eq!(at("/role").actual, Some(json!("root")));
+        assert_eq!(at("/extra").expected, "no additional properties");
+        assert_eq!(violations.len(), 6);
+
+        assert_eq!(
+            schema.validate(&json!([1])),
+            vec![SchemaViolation {
+                path: String::new(),
+                expected: "type object".to_string(),
+                actual: Some(json!([1])),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_combinators() {
+        let schema = JsonSchema::compile(&json!({
+            "oneOf": [{"type": "integer"}, {"type": "number", "multipleOf": 0.5}],
+            "not": {"const": 3}
+        }))
+        .unwrap();
+        assert!(schema.is_valid(&json!(1.5)));
+        // 2 is both an integer and a multiple of 0.5
+        assert_eq!(schema.validate(&json!(2)).len(), 1);
+        assert_eq!(schema.validate(&json!(3)).len(), 2);
+
+        let schema = JsonSchema::compile(&json!({"anyOf": [{"type": "null"}, {"type": "string", "maxLength": 2}]})).unwrap();
+        assert!(schema.is_valid(&Value::Null));
+        assert!(!schema.is_valid(&json!("abc")));
+    }
+
+    #[test]
+    fn test_pointer_segments_are_escaped() {
+        let schema = JsonSchema::compile(&json!({"properties": {"a/b~c": {"type": "string"}}})).unwrap();
+        assert_eq!(schema.validate(&json!({"a/b~c": 1}))[0].path, "/a~1b~0c");
+    }
+
+    #[test]
+    fn test_unsupported_or_malformed_schemas_are_rejected() {
+        for schema in [
+            json!({"pattern": "^a"}),
+            json!({"properties": {"x": {"$ref": "#/defs/x"}}}),
+            json!({"type": "text"}),
+            json!({"minLength": -1}),
+            json!({"anyOf": []}),
+            json!(42),
+        ] {
+            assert!(matches!(JsonSchema::compile(&schema), Err(DocumentError::InvalidSchema(_))), "{schema} accepted");
+        }
+    }
+}
@@ -0,0 +1,290 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Export and import of whole document stores
+//!
+//! A snapshot is JSON lines: a header, then for every namespace a namespace line
+//! followed by each of its collections (with schema, compression codec and
+//! covering indexes) and the collection's documents. The header carries the change
+//! feed sequence the snapshot was taken at, so a replica bootstrapped from it knows
+//! where to resume streaming; stores without a change feed export sequence 0.
+
+use super::compression::CompressionCodec;
+use super::{CollectionName, Document, DocumentError, DocumentId, DocumentResult, DocumentStorage, Namespace};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{BufRead, Write};
+
+/// Version of the snapshot format written by [`export_snapshot`]
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 1;
+
+/// One line of a snapshot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum SnapshotLine {
+    Header {
+        format_version: u32,
+        sequence: u64,
+        taken_at_ms: u64,
+    },
+    Namespace {
+        name: String,
+    },
+    Collection {
+        name: String,
+        schema: Option<Value>,
+        /// Absent from snapshots taken before collections had codecs
+        #[serde(default)]
+        compression: CompressionCodec,
+        covering_indexes: Vec<Vec<String>>,
+    },
+    Document {
+        id: DocumentId,
+        content: Value,
+    },
+}
+
+/// What a snapshot holds
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotSummary {
+    /// Change feed sequence the snapshot includes every change up to
+    pub sequence: u64,
+    /// When the snapshot was taken, in milliseconds since the Unix epoch
+    pub taken_at_ms: u64,
+    pub namespaces: usize,
+    pub collections: usize,
+    pub documents: usize,
+}
+
+/// Write every namespace, collection and document in `storage` to `out`
+///
+/// The caller keeps writers out for the duration if the snapshot must be
+/// consistent; [`ChangeFeedStorage::export_snapshot`](super::ChangeFeedStorage::export_snapshot) does.
+pub fn export_snapshot(storage: &dyn DocumentStorage, sequence: u64, out: &mut dyn Write) -> DocumentResult<SnapshotSummary> {
+    let mut summary = SnapshotSummary {
+        sequence,
+        taken_at_ms: now_ms(),
+        ..SnapshotSummary::default()
+    };
+    write_line(
+        out,
+        &SnapshotLine::Header {
+            format_version: SNAPSHOT_FORMAT_VERSION,
+            sequence,
+            taken_at_ms: summary.taken_at_ms,
+        },
+    )?;
+
+    for namespace in storage.list_namespaces()? {
+        let scoped = storage.in_namespace(&namespace);
+        write_line(out, &SnapshotLine::Namespace { name: namespace.to_string() })?;
+        summary.namespaces += 1;
+
+        for collection in scoped.list_collections()? {
+            write_line(
+                out,
+                &SnapshotLine::Collection {
+                    name: collection.to_string(),
+                    schema: scoped.get_schema(&collection)?,
+                    compression: scoped.get_compression(&collection)?,
+                    covering_indexes: scoped.covering_indexes(&collection)?,
+                },
+            )?;
+            summary.collections += 1;
+
+            for id in scoped.list_documents(&collection)? {
+                // Listed documents can only vanish under a concurrent writer
+                if let Some(document) = scoped.get_document(&collection, &id)? {
+                    write_line(out, &SnapshotLine::Document { id, content: document.content })?;
+                    summary.documents += 1;
+                }
+            }
+        }
+    }
+
+    out.flush()?;
+    Ok(summary)
+}
+
+/// Load a snapshot into `storage`, replacing documents with the same ID
+///
+/// Existing data the snapshot does not mention is left alone; see [`clear_storage`]
+/// to start from an empty store.
+pub fn import_snapshot(storage: &dyn DocumentStorage, input: impl BufRead) -> DocumentResult<SnapshotSummary> {
+    let mut summary = None;
+    let mut namespace = None;
+    let mut collection = None;
+
+    for (index, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let invalid = |message: String| DocumentError::InvalidSnapshot(format!("line {}: {message}", index + 1));
+        let parsed: SnapshotLine = serde_json::from_str(&line).map_err(|e| invalid(e.to_string()))?;
+
+        let Some(summary) = summary.as_mut() else {
+            let SnapshotLine::Header {
+                format_version,
+                sequence,
+                taken_at_ms,
+            } = parsed
+            else {
+                return Err(invalid("snapshot does not start with a header".to_string()));
+            };
+            if format_version != SNAPSHOT_FORMAT_VERSION {
+                return Err(invalid(format!("unsupported format version {format_version}")));
+            }
+            summary = Some(SnapshotSummary {
+                sequence,
+                taken_at_ms,
+                ..SnapshotSummary::default()
+            });
+            continue;
+        };
+
+        match parsed {
+            SnapshotLine::Header { .. } => return Err(invalid("unexpected second header".to_string())),
+            SnapshotLine::Namespace { name } => {
+                let name = Namespace::new(name)?;
+                storage.create_namespace(&name)?;
+                namespace = Some(storage.in_namespace(&name));
+                collection = None;
+                summary.namespaces += 1;
+            }
+            SnapshotLine::Collection {
+                name,
+                schema,
+                compression,
+                covering_indexes,
+            } => {
+                let scoped = namespace.as_ref().ok_or_else(|| invalid("collection outside a namespace".to_string()))?;
+                let name = CollectionName::new(name);
+                scoped.create_collection(&name)?;
+                scoped.set_schema(&name, schema.as_ref())?;
+                scoped.set_compression(&name, compression)?;
+                for paths in &covering_indexes {
+                    scoped.create_covering_index(&name, paths)?;
+                }
+                collection = Some(name);
+                summary.collections += 1;
+            }
+            SnapshotLine::Document { id, content } => {
+                let (Some(scoped), Some(name)) = (namespace.as_ref(), collection.as_ref()) else {
+                    return Err(invalid("document outside a collection".to_string()));
+                };
+                put_document(scoped.as_ref(), name, id, content)?;
+                summary.documents += 1;
+            }
+        }
+    }
+
+    summary.ok_or_else(|| DocumentError::InvalidSnapshot("snapshot is empty".to_string()))
+}
+
+/// Delete every collection and every namespace but the default one
+pub fn clear_storage(storage: &dyn DocumentStorage) -> DocumentResult<()> {
+    for namespace in storage.list_namespaces()? {
+        if namespace.is_default() {
+            let scoped = storage.in_namespace(&namespace);
+            for collection in scoped.list_collections()? {
+                scoped.delete_collection(&collection)?;
+            }
+        } else {
+            storage.drop_namespace(&namespace, true)?;
+        }
+    }
+    Ok(())
+}
+
+/// Create the document or replace the content of the existing one, keeping its metadata
+pub(crate) fn put_document(storage: &dyn DocumentStorage, collection: &CollectionName, id: DocumentId, content: Value) -> DocumentResult<()> {
+    match storage.get_document(collection, &id)? {
+        Some(mut document) => {
+            document.content = content;
+            storage.update_document(collection, document)
+        }
+        None => storage.create_document(collection, Document::with_id(id, content)).map(|_| ()),
+    }
+}
+
+fn write_line(out: &mut dyn Write, line: &SnapshotLine) -> DocumentResult<()> {
+    serde_json::to_writer(&mut *out, line)?;
+    out.write_all(b"\n")?;
+    Ok(())
+}
+
+pub(crate) fn now_ms() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::DocumentStore;
+    use crate::state::db_interface::Database;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn store() -> DocumentStore {
+        DocumentStore::new(Arc::new(Database::new_in_memory().unwrap()))
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let source = store();
+        let users = CollectionName::new("users");
+        let alice = source.create_document(&users, Document::new(json!({"name": "Alice"}))).unwrap();
+        source.set_schema(&users, Some(&json!({"type": "object"}))).unwrap();
+        source.create_covering_index(&users, &["name".to_string()]).unwrap();
+        let tenant = source.in_namespace(&Namespace::new("tenant-a").unwrap());
+        tenant.create_document(&CollectionName::new("orders"), Document::new(json!({"total": 3}))).unwrap();
+
+        let mut exported = Vec::new();
+        let summary = export_snapshot(&source, 42, &mut exported).unwrap();
+        assert_eq!((summary.sequence, summary.namespaces, summary.collections, summary.documents), (42, 2, 2, 2));
+
+        let target = store();
+        let imported = import_snapshot(&target, exported.as_slice()).unwrap();
+        assert_eq!(imported, summary);
+        assert_eq!(target.get_document(&users, &alice).unwrap().unwrap().content, json!({"name": "Alice"}));
+        assert_eq!(target.get_schema(&users).unwrap(), Some(json!({"type": "object"})));
+        assert_eq!(target.covering_indexes(&users).unwrap(), [["name".to_string()]]);
+        let tenant = target.in_namespace(&Namespace::new("tenant-a").unwrap());
+        assert_eq!(tenant.count_documents(&CollectionName::new("orders")).unwrap(), 1);
+
+        clear_storage(&target).unwrap();
+        assert_eq!(target.list_namespaces().unwrap(), [Namespace::default()]);
+        assert!(target.list_collections().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rejects_malformed_snapshots() {
+        let target = store();
+        assert!(matches!(import_snapshot(&target, &b""[..]), Err(DocumentError::InvalidSnapshot(_))));
+
+        let headless = format!("{}\n", json!({"kind": "namespace", "name": "default"}));
+        assert!(matches!(import_snapshot(&target, headless.as_bytes()), Err(DocumentError::InvalidSnapshot(_))));
+
+        let orphan = format!(
+            "{}\n{}\n",
+            json!({"kind": "header", "format_version": 1, "sequence": 0, "taken_at_ms": 0}),
+            json!({"kind": "document", "id": DocumentId::new(), "content": {}})
+        );
+        let error = import_snapshot(&target, orphan.as_bytes()).unwrap_err();
+        assert!(error.to_string().contains("line 2: document outside a collection"), "{error}");
+    }
+}
@@ -18,10 +18,49 @@
 //!
 //! This module provides the main document storage interface that builds on top
 //! of the key-value database interface to provide document-oriented operations.
-
-use super::{CollectionName, Document, DocumentError, DocumentId, DocumentResult};
-use crate::state::db_interface::DatabaseInterface;
+//!
+//! ## Key layout
+//!
+//! Namespace names never contain `:`, so the first segment after the key prefix
+//! always identifies the namespace:
+//!
+//! - `namespaces`: list of namespaces
+//! - `ns:{namespace}`: namespace metadata
+//! - `collections:{namespace}`: list of the namespace's collections
+//! - `col:{namespace}:{collection}`: collection metadata
+//! - `col_docs:{namespace}:{collection}`: list of the collection's document IDs
+//! - `schema:{namespace}:{collection}`: the collection's JSON Schema, if it has one
+//! - `codec:{namespace}:{collection}`: the codec the collection's documents are compressed with, if any
+//! - `idx:{namespace}:{collection}`: paths of the collection's covering indexes
+//! - `idx_entries:{namespace}:{collection}:{paths}`: a covering index's entries, `paths` comma-joined
+//! - `doc:{namespace}:{collection}:{id}`: document
+//! - `col_history:{namespace}:{collection}`: IDs of the collection's documents that have recorded versions
+//! - `doc_versions:{namespace}:{collection}:{id}`: the document's versions, oldest first
+//! - `history_horizon`: earliest time, in nanoseconds since the Unix epoch, reads can go back to
+//!
+//! Document bodies and version lists are compressed with their collection's codec;
+//! see the [`compression`](super::compression) module for the stored format.
+//!
+//! Every write records the new version of each document it touches so that reads
+//! can be made as of a past time; see [`DocumentStorage::read_snapshot`].
+//!
+//! Databases written before namespaces existed used the same keys without the
+//! namespace segment and a single `collections` list. [`DocumentStore::open`]
+//! moves such data into the `default` namespace once.
+
+use super::compression::{self, CompressionCodec, RecompressionReport};
+use super::history::{self, DocumentVersion, HISTORY_HORIZON_KEY, HistoryClock, SnapshotPin};
+use super::projection::{ProjectedValue, Projection};
+use super::{CollectionName, Document, DocumentError, DocumentId, DocumentResult, Namespace};
+use crate::compaction::strategy::DeadSpaceStrategy;
+use crate::state::db_interface::{BatchOp, DatabaseInterface, SegmentCompaction};
+use crate::statistics::CollectionSpaceUsage;
+use crate::storage_engine::generate_timestamp;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::Duration;
 
 /// Document storage interface
 pub trait DocumentStorage: Send + Sync {
@@ -31,12 +70,27 @@ pub trait DocumentStorage: Send + Sync {
     /// Get a document by ID from a collection
     fn get_document(&self, collection: &CollectionName, id: &DocumentId) -> DocumentResult<Option<Document>>;
 
+    /// Get a document read by a collection scan, which should not displace the hot documents
+    /// from any cache of the underlying database
+    fn get_document_for_scan(&self, collection: &CollectionName, id: &DocumentId) -> DocumentResult<Option<Document>> {
+        self.get_document(collection, id)
+    }
+
     /// Update an existing document
     fn update_document(&self, collection: &CollectionName, document: Document) -> DocumentResult<()>;
 
     /// Delete a document by ID
     fn delete_document(&self, collection: &CollectionName, id: &DocumentId) -> DocumentResult<bool>;
 
+    /// Update an existing document if it is still at `expected_version`, returning its new version
+    ///
+    /// The check and the write are one commit, so of two writers expecting the same version
+    /// one succeeds and the other fails with [`DocumentError::VersionConflict`].
+    fn update_document_if_version(&self, collection: &CollectionName, document: Document, expected_version: u64) -> DocumentResult<u64>;
+
+    /// Delete a document by ID if it is still at `expected_version`, returning whether it existed
+    fn delete_document_if_version(&self, collection: &CollectionName, id: &DocumentId, expected_version: u64) -> DocumentResult<bool>;
+
     /// Check if a document exists
     fn document_exists(&self, collection: &CollectionName, id: &DocumentId) -> DocumentResult<bool>;
 
@@ -52,52 +106,364 @@ pub trait DocumentStorage: Send + Sync {
     /// Delete a collection and all its documents
     fn delete_collection(&self, collection: &CollectionName) -> DocumentResult<bool>;
 
+    /// Rename a collection with its documents, history, schema, codec and covering indexes
+    ///
+    /// Fails with [`DocumentError::CollectionNotFound`] if `from` does not exist and with
+    /// [`DocumentError::CollectionAlreadyExists`] if `to` does. Readers find the collection
+    /// under at least one of the two names throughout.
+    fn rename_collection(&self, from: &CollectionName, to: &CollectionName) -> DocumentResult<()>;
+
     /// List all collections
     fn list_collections(&self) -> DocumentResult<Vec<CollectionName>>;
 
     /// Check if a collection exists
     fn collection_exists(&self, collection: &CollectionName) -> DocumentResult<bool>;
+
+    /// Store the JSON Schema documents in a collection must match, creating the
+    /// collection if needed, or remove it with `None`
+    fn set_schema(&self, collection: &CollectionName, schema: Option<&Value>) -> DocumentResult<()>;
+
+    /// The collection's JSON Schema, if one is set
+    fn get_schema(&self, collection: &CollectionName) -> DocumentResult<Option<Value>>;
+
+    /// Compress the documents written to a collection from now on with `codec`;
+    /// documents already stored keep the codec they were written with
+    fn set_compression(&self, collection: &CollectionName, codec: CompressionCodec) -> DocumentResult<()>;
+
+    /// Codec a collection's new documents are compressed with
+    fn get_compression(&self, collection: &CollectionName) -> DocumentResult<CompressionCodec>;
+
+    /// Store the given documents and their histories again under the collection's
+    /// current codec
+    ///
+    /// Contents are unchanged, so the rewrite records no versions. Documents that no
+    /// longer exist are skipped.
+    fn rewrite_documents(&self, collection: &CollectionName, ids: &[DocumentId]) -> DocumentResult<RecompressionReport>;
+
+    /// Create a covering index holding the values of `paths` for every document in the
+    /// collection, returning whether it was created
+    ///
+    /// The index is filled from the existing documents and kept current as documents are
+    /// written and deleted.
+    fn create_covering_index(&self, collection: &CollectionName, paths: &[String]) -> DocumentResult<bool>;
+
+    /// Drop the covering index over `paths`, returning whether it existed
+    fn drop_covering_index(&self, collection: &CollectionName, paths: &[String]) -> DocumentResult<bool>;
+
+    /// Paths of each covering index on the collection
+    fn covering_indexes(&self, collection: &CollectionName) -> DocumentResult<Vec<Vec<String>>>;
+
+    /// Read the covering index over `paths` without reading the documents it covers
+    fn read_covering_index(&self, collection: &CollectionName, paths: &[String]) -> DocumentResult<Option<CoveringIndex>>;
+
+    /// Namespace the collections of this storage live in
+    fn namespace(&self) -> &Namespace;
+
+    /// The same underlying storage scoped to another namespace
+    fn in_namespace(&self, namespace: &Namespace) -> Arc<dyn DocumentStorage>;
+
+    /// Create a namespace (if it doesn't exist), returning whether it was created
+    fn create_namespace(&self, namespace: &Namespace) -> DocumentResult<bool>;
+
+    /// List all namespaces; the default namespace is always included
+    fn list_namespaces(&self) -> DocumentResult<Vec<Namespace>>;
+
+    /// Drop a namespace, returning whether it existed
+    ///
+    /// A namespace that still has collections is only dropped with `force`, which
+    /// deletes its collections and their documents as well. The default namespace
+    /// cannot be dropped.
+    fn drop_namespace(&self, namespace: &Namespace, force: bool) -> DocumentResult<bool>;
+
+    /// Apply a set of writes as one commit, creating documents that do not exist yet
+    fn commit_writes(&self, writes: Vec<DocumentWrite>) -> DocumentResult<()> {
+        for write in writes {
+            match write.content {
+                Some(content) => {
+                    let document = Document::with_id(write.id.clone(), content);
+                    if self.document_exists(&write.collection, &write.id)? {
+                        self.update_document(&write.collection, document)?;
+                    } else {
+                        self.create_document(&write.collection, document)?;
+                    }
+                }
+                None => {
+                    self.delete_document(&write.collection, &write.id)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Fix the snapshot a read as of `as_of`, in nanoseconds since the Unix epoch, runs
+    /// against
+    ///
+    /// Reads of the same query should all use the returned snapshot: it includes every
+    /// commit up to `as_of` that has completed and none that is still in flight. Fails
+    /// with [`DocumentError::HistoryUnavailable`] if `as_of` precedes the retained history.
+    fn read_snapshot(&self, as_of: u64) -> DocumentResult<u64>;
+
+    /// Get a document as it was at a snapshot from [`read_snapshot`](Self::read_snapshot)
+    fn get_document_as_of(&self, collection: &CollectionName, id: &DocumentId, snapshot: u64) -> DocumentResult<Option<Document>>;
+
+    /// List the IDs of the documents a collection held at a snapshot from
+    /// [`read_snapshot`](Self::read_snapshot)
+    fn list_documents_as_of(&self, collection: &CollectionName, snapshot: u64) -> DocumentResult<Vec<DocumentId>>;
+
+    /// Pin a snapshot of every collection at the last completed commit
+    ///
+    /// History visible at the snapshot is kept, by writes and compaction alike, until
+    /// the pin is released or has been held longer than `max_age`.
+    fn pin_snapshot(&self, max_age: Duration) -> DocumentResult<SnapshotPin>;
+
+    /// Logical and on-disk size of a collection, with its documents, history and indexes
+    fn space_usage(&self, collection: &CollectionName) -> DocumentResult<CollectionSpaceUsage>;
+
+    /// Reclaim the space of deleted and overwritten documents in every namespace
+    ///
+    /// Drops the history of documents deleted before the earliest time reads can go
+    /// back to, since no read can observe them any more, then rewrites the storage
+    /// segments `strategy` picks without their dead values.
+    fn compact(&self, strategy: &DeadSpaceStrategy) -> DocumentResult<DocumentCompaction>;
+
+    /// Flush buffered writes to durable storage
+    fn flush(&self) -> DocumentResult<()> {
+        Ok(())
+    }
+}
+
+/// One write of a commit; `None` content deletes the document
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentWrite {
+    pub collection: CollectionName,
+    pub id: DocumentId,
+    pub content: Option<Value>,
+}
+
+/// Values of a set of paths for every document in a collection
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoveringIndex {
+    pub paths: Vec<String>,
+    /// Each document's value at every path, in path order
+    pub entries: Vec<(DocumentId, Vec<ProjectedValue>)>,
+}
+
+/// Outcome of [`DocumentStorage::compact`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocumentCompaction {
+    /// Deleted documents whose history was dropped
+    pub tombstones_dropped: u64,
+    /// Versions dropped with them or pruned from the history of other documents
+    pub versions_dropped: u64,
+    pub segments: SegmentCompaction,
 }
 
+/// Storage key of the namespace list
+const NAMESPACES_KEY: &[u8] = b"namespaces";
+
+/// Storage key of the collection list written before namespaces existed
+const LEGACY_COLLECTIONS_KEY: &[u8] = b"collections";
+
 /// Document storage implementation using the database interface
 pub struct DocumentStore {
     db: Arc<dyn DatabaseInterface>,
+    namespace: Namespace,
+    clock: Arc<HistoryClock>,
+    /// How long versions are kept; forever if `None`
+    history_retention: Option<Duration>,
 }
 
 impl DocumentStore {
-    /// Create a new document store
+    /// Create a new document store in the default namespace
+    ///
+    /// Data written before namespaces existed is not visible through a store created
+    /// this way; use [`open`](Self::open) for databases that may hold such data.
     pub fn new(db: Arc<dyn DatabaseInterface>) -> Self {
-        Self { db }
+        Self {
+            db,
+            namespace: Namespace::default(),
+            clock: Arc::new(HistoryClock::new()),
+            history_retention: None,
+        }
+    }
+
+    /// Open a document store in the default namespace, first moving data written
+    /// before namespaces existed into the default namespace
+    ///
+    /// History starts the first time a database is opened; reads cannot go back
+    /// further than that.
+    pub fn open(db: Arc<dyn DatabaseInterface>) -> DocumentResult<Self> {
+        let store = Self::new(db);
+        store.migrate_legacy_layout()?;
+        store.stored_horizon(store.clock.snapshot(u64::MAX))?;
+        Ok(store)
+    }
+
+    /// Keep document versions for `retention` only
+    ///
+    /// Versions older than that are dropped the next time their document is written,
+    /// and reads as of earlier times fail.
+    pub fn with_history_retention(mut self, retention: Duration) -> Self {
+        self.history_retention = Some(retention);
+        self
     }
 
     /// Generate storage key for a document
     fn document_key(&self, collection: &CollectionName, id: &DocumentId) -> Vec<u8> {
-        format!("doc:{}:{}", collection.as_str(), id).into_bytes()
+        format!("doc:{}:{}:{}", self.namespace, collection.as_str(), id).into_bytes()
     }
 
     /// Generate storage key for collection metadata
     fn collection_key(&self, collection: &CollectionName) -> Vec<u8> {
-        format!("col:{}", collection.as_str()).into_bytes()
+        format!("col:{}:{}", self.namespace, collection.as_str()).into_bytes()
     }
 
     /// Generate storage key for collection document list
     fn collection_docs_key(&self, collection: &CollectionName) -> Vec<u8> {
-        format!("col_docs:{}", collection.as_str()).into_bytes()
+        format!("col_docs:{}:{}", self.namespace, collection.as_str()).into_bytes()
+    }
+
+    /// Generate storage key for a collection's schema
+    fn schema_key(&self, collection: &CollectionName) -> Vec<u8> {
+        format!("schema:{}:{}", self.namespace, collection.as_str()).into_bytes()
+    }
+
+    /// Generate storage key for a collection's compression codec
+    fn compression_key(&self, collection: &CollectionName) -> Vec<u8> {
+        format!("codec:{}:{}", self.namespace, collection.as_str()).into_bytes()
+    }
+
+    /// Generate storage key for the paths of a collection's covering indexes
+    fn covering_indexes_key(&self, collection: &CollectionName) -> Vec<u8> {
+        format!("idx:{}:{}", self.namespace, collection.as_str()).into_bytes()
     }
 
-    /// Generate storage key for global collections list
+    /// Generate storage key for the entries of a covering index
+    fn covering_index_entries_key(&self, collection: &CollectionName, paths: &[String]) -> Vec<u8> {
+        format!("idx_entries:{}:{}:{}", self.namespace, collection.as_str(), paths.join(",")).into_bytes()
+    }
+
+    /// Generate storage key for a document's versions
+    fn document_versions_key(&self, collection: &CollectionName, id: &DocumentId) -> Vec<u8> {
+        format!("doc_versions:{}:{}:{}", self.namespace, collection.as_str(), id).into_bytes()
+    }
+
+    /// Generate storage key for the IDs of a collection's documents with versions
+    fn collection_history_key(&self, collection: &CollectionName) -> Vec<u8> {
+        format!("col_history:{}:{}", self.namespace, collection.as_str()).into_bytes()
+    }
+
+    /// Generate storage key for the namespace's collections list
     fn collections_list_key(&self) -> Vec<u8> {
-        b"collections".to_vec()
+        format!("collections:{}", self.namespace).into_bytes()
+    }
+
+    /// Generate storage key for namespace metadata
+    fn namespace_key(namespace: &Namespace) -> Vec<u8> {
+        format!("ns:{}", namespace).into_bytes()
+    }
+
+    /// The same database scoped to `namespace`
+    fn scoped(&self, namespace: &Namespace) -> Self {
+        Self {
+            db: self.db.clone(),
+            namespace: namespace.clone(),
+            clock: self.clock.clone(),
+            history_retention: self.history_retention,
+        }
+    }
+
+    /// Namespaces recorded in the namespace list
+    fn stored_namespaces(&self) -> DocumentResult<Vec<Namespace>> {
+        match self.db.get(NAMESPACES_KEY)? {
+            Some(data) => Ok(serde_json::from_slice(&data)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Move collections written before namespaces existed into the default namespace
+    ///
+    /// Runs once: the namespace list doubles as the marker that the database uses the
+    /// namespaced layout. Everything is applied in one batch.
+    fn migrate_legacy_layout(&self) -> DocumentResult<()> {
+        if self.db.contains(NAMESPACES_KEY)? {
+            return Ok(());
+        }
+
+        let default = Namespace::default();
+        let target = self.scoped(&default);
+        let mut ops = Vec::new();
+        let mut written = HashSet::new();
+        let mut legacy_keys = Vec::new();
+
+        let legacy_collections = match self.db.get(LEGACY_COLLECTIONS_KEY)? {
+            Some(data) => self.deserialize_collection_list(&data)?,
+            None => Vec::new(),
+        };
+        for collection in &legacy_collections {
+            let mut moves = vec![
+                (format!("col:{}", collection.as_str()).into_bytes(), target.collection_key(collection)),
+                (format!("col_docs:{}", collection.as_str()).into_bytes(), target.collection_docs_key(collection)),
+            ];
+            if let Some(data) = self.db.get(&moves[1].0)? {
+                for id in self.deserialize_doc_list(&data)? {
+                    moves.push((format!("doc:{}:{}", collection.as_str(), id).into_bytes(), target.document_key(collection, &id)));
+                }
+            }
+
+            for (legacy_key, key) in moves {
+                if let Some(value) = self.db.get(&legacy_key)? {
+                    written.insert(key.clone());
+                    ops.push(BatchOp::Put { key, value });
+                    legacy_keys.push(legacy_key);
+                }
+            }
+        }
+
+        if !legacy_collections.is_empty() {
+            ops.push(BatchOp::Put {
+                key: target.collections_list_key(),
+                value: self.serialize_collection_list(&legacy_collections)?,
+            });
+        }
+        // A legacy key can equal a new key when an old collection name contained `:`
+        ops.extend(legacy_keys.into_iter().filter(|key| !written.contains(key)).map(|key| BatchOp::Delete { key }));
+        ops.push(BatchOp::Delete {
+            key: LEGACY_COLLECTIONS_KEY.to_vec(),
+        });
+        ops.push(BatchOp::Put {
+            key: Self::namespace_key(&default),
+            value: Self::namespace_metadata(&default)?,
+        });
+        ops.push(BatchOp::Put {
+            key: NAMESPACES_KEY.to_vec(),
+            value: serde_json::to_vec(&[default])?,
+        });
+
+        self.db.batch(ops)?;
+        Ok(())
     }
 
-    /// Serialize document to bytes
-    fn serialize_document(&self, document: &Document) -> DocumentResult<Vec<u8>> {
-        Ok(serde_json::to_vec(document)?)
+    /// Metadata stored for a namespace
+    fn namespace_metadata(namespace: &Namespace) -> DocumentResult<Vec<u8>> {
+        let metadata = serde_json::json!({
+            "name": namespace.as_str(),
+            "created_at": std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        });
+        Ok(serde_json::to_vec(&metadata)?)
+    }
+
+    /// Serialize document to bytes compressed with the collection's codec
+    fn serialize_document(&self, collection: &CollectionName, document: &Document) -> DocumentResult<Vec<u8>> {
+        self.get_compression(collection)?.encode(serde_json::to_vec(document)?)
     }
 
     /// Deserialize document from bytes
     fn deserialize_document(&self, data: &[u8]) -> DocumentResult<Document> {
-        Ok(serde_json::from_slice(data)?)
+        Ok(serde_json::from_slice(&compression::decode(data)?)?)
     }
 
     /// Serialize document ID list to bytes
@@ -122,7 +488,11 @@ impl DocumentStore {
 
     /// Add document ID to collection's document list
     fn add_to_collection_docs(&self, collection: &CollectionName, id: &DocumentId) -> DocumentResult<()> {
-        let key = self.collection_docs_key(collection);
+        self.add_to_doc_list(self.collection_docs_key(collection), id)
+    }
+
+    /// Add document ID to the document list stored under `key`
+    fn add_to_doc_list(&self, key: Vec<u8>, id: &DocumentId) -> DocumentResult<()> {
         let mut doc_ids = if let Some(data) = self.db.get(&key)? { self.deserialize_doc_list(&data)? } else { Vec::new() };
 
         if !doc_ids.contains(id) {
@@ -147,40 +517,137 @@ impl DocumentStore {
         Ok(())
     }
 
-    /// Add collection to global collections list
-    fn add_to_collections_list(&self, collection: &CollectionName) -> DocumentResult<()> {
-        let key = self.collections_list_key();
-        let mut collections = if let Some(data) = self.db.get(&key)? {
-            self.deserialize_collection_list(&data)?
-        } else {
-            Vec::new()
+    /// Store the entries of a covering index
+    fn write_covering_index(&self, collection: &CollectionName, index: &CoveringIndex) -> DocumentResult<()> {
+        self.db.put(self.covering_index_entries_key(collection, &index.paths), serde_json::to_vec(index)?)?;
+        Ok(())
+    }
+
+    /// Bring every covering index on the collection up to date with a document's new
+    /// content, or remove the document from them when `content` is `None`
+    fn update_covering_indexes(&self, collection: &CollectionName, id: &DocumentId, content: Option<&Value>) -> DocumentResult<()> {
+        for paths in self.covering_indexes(collection)? {
+            let Some(mut index) = self.read_covering_index(collection, &paths)? else {
+                continue;
+            };
+            let position = index.entries.iter().position(|(entry_id, _)| entry_id == id);
+            match (content, position) {
+                (Some(content), Some(position)) => index.entries[position].1 = Projection::new(&paths)?.project(content),
+                (Some(content), None) => index.entries.push((id.clone(), Projection::new(&paths)?.project(content))),
+                (None, Some(position)) => {
+                    index.entries.remove(position);
+                }
+                (None, None) => continue,
+            }
+            self.write_covering_index(collection, &index)?;
+        }
+        Ok(())
+    }
+
+    /// Earliest time history is kept from, recording `started` if history has not
+    /// started yet
+    fn stored_horizon(&self, started: u64) -> DocumentResult<u64> {
+        match self.db.get(HISTORY_HORIZON_KEY)? {
+            Some(data) => Ok(serde_json::from_slice(&data)?),
+            None => {
+                self.db.put(HISTORY_HORIZON_KEY.to_vec(), serde_json::to_vec(&started)?)?;
+                Ok(started)
+            }
+        }
+    }
+
+    /// Earliest time reads at `now` can go back to
+    ///
+    /// Never later than the oldest pinned snapshot.
+    fn retained_from(&self, now: u64, started: u64) -> DocumentResult<u64> {
+        let horizon = self.stored_horizon(started)?;
+        let retained = match self.history_retention {
+            Some(retention) => horizon.max(now.saturating_sub(retention.as_nanos() as u64)),
+            None => horizon,
         };
+        Ok(match self.clock.oldest_pin() {
+            Some(pinned) => retained.min(pinned),
+            None => retained,
+        })
+    }
 
-        if !collections.contains(collection) {
-            collections.push(collection.clone());
-            let serialized = self.serialize_collection_list(&collections)?;
-            self.db.put(key, serialized)?;
+    /// A document's recorded versions, oldest first
+    fn document_versions(&self, collection: &CollectionName, id: &DocumentId) -> DocumentResult<Vec<DocumentVersion>> {
+        match self.db.get(&self.document_versions_key(collection, id))? {
+            Some(data) => Ok(serde_json::from_slice(&compression::decode(&data)?)?),
+            None => Ok(Vec::new()),
         }
+    }
 
+    /// Store a document's versions compressed with the collection's codec
+    fn write_versions(&self, collection: &CollectionName, id: &DocumentId, versions: &[DocumentVersion]) -> DocumentResult<()> {
+        let encoded = self.get_compression(collection)?.encode(serde_json::to_vec(versions)?)?;
+        self.db.put(self.document_versions_key(collection, id), encoded)?;
         Ok(())
     }
 
-    /// Remove collection from global collections list
-    fn remove_from_collections_list(&self, collection: &CollectionName) -> DocumentResult<()> {
-        let key = self.collections_list_key();
-        if let Some(data) = self.db.get(&key)? {
-            let mut collections = self.deserialize_collection_list(&data)?;
-            collections.retain(|col| col != collection);
-            let serialized = self.serialize_collection_list(&collections)?;
-            self.db.put(key, serialized)?;
+    /// Record the document's version written by the commit at `committed_at`, or
+    /// a tombstone for `None`
+    ///
+    /// Must run before the write itself so that a concurrent time-travel read that
+    /// sees the new document also sees its version.
+    fn record_version(&self, collection: &CollectionName, id: &DocumentId, document: Option<&Document>, committed_at: u64) -> DocumentResult<()> {
+        let mut versions = self.document_versions(collection, id)?;
+        if versions.is_empty() {
+            // A document that predates history has been unchanged since it started
+            if let Some(current) = self.get_document(collection, id)? {
+                let started = self.stored_horizon(committed_at - 1)?;
+                versions.push(DocumentVersion {
+                    committed_at: started,
+                    document: Some(current),
+                });
+            }
+            self.add_to_doc_list(self.collection_history_key(collection), id)?;
+        }
+        versions.push(DocumentVersion {
+            committed_at,
+            document: document.cloned(),
+        });
+        history::prune(&mut versions, self.retained_from(committed_at, committed_at - 1)?);
+        self.write_versions(collection, id, &versions)
+    }
+
+    /// Prune the collection's document histories to what reads at or after `horizon`
+    /// can see, forgetting documents deleted before it
+    fn prune_history(&self, collection: &CollectionName, horizon: u64, report: &mut DocumentCompaction) -> DocumentResult<()> {
+        let list_key = self.collection_history_key(collection);
+        let Some(data) = self.db.get(&list_key)? else {
+            return Ok(());
+        };
+        let mut ids = self.deserialize_doc_list(&data)?;
+
+        let recorded = ids.len();
+        let mut kept = Vec::with_capacity(ids.len());
+        for id in ids.drain(..) {
+            let mut versions = self.document_versions(collection, &id)?;
+            let before = versions.len();
+            let deleted = versions.last().is_some_and(|version| version.document.is_none());
+            history::prune(&mut versions, horizon);
+            report.versions_dropped += (before - versions.len()) as u64;
+
+            if versions.is_empty() {
+                self.db.delete(&self.document_versions_key(collection, &id))?;
+                report.tombstones_dropped += u64::from(deleted);
+            } else {
+                if versions.len() != before {
+                    self.write_versions(collection, &id, &versions)?;
+                }
+                kept.push(id);
+            }
         }
 
+        if kept.len() != recorded {
+            self.db.put(list_key, self.serialize_doc_list(&kept)?)?;
+        }
         Ok(())
     }
-}
 
-impl DocumentStorage for DocumentStore {
-    fn create_document(&self, collection: &CollectionName, mut document: Document) -> DocumentResult<DocumentId> {
+    fn create_at(&self, collection: &CollectionName, mut document: Document, committed_at: u64) -> DocumentResult<DocumentId> {
         // Ensure collection exists
         self.create_collection(collection)?;
 
@@ -192,57 +659,184 @@ impl DocumentStorage for DocumentStore {
 
         // Update metadata
         document.metadata.update();
+        self.record_version(collection, &document.id, Some(&document), committed_at)?;
 
         // Store document
-        let serialized = self.serialize_document(&document)?;
+        let serialized = self.serialize_document(collection, &document)?;
         self.db.put(doc_key, serialized)?;
 
         // Add to collection's document list
         self.add_to_collection_docs(collection, &document.id)?;
+        self.update_covering_indexes(collection, &document.id, Some(&document.content))?;
 
         Ok(document.id)
     }
 
-    fn get_document(&self, collection: &CollectionName, id: &DocumentId) -> DocumentResult<Option<Document>> {
-        let key = self.document_key(collection, id);
-        match self.db.get(&key)? {
-            Some(data) => {
-                let document = self.deserialize_document(&data)?;
-                Ok(Some(document))
-            }
-            None => Ok(None),
-        }
-    }
-
-    fn update_document(&self, collection: &CollectionName, mut document: Document) -> DocumentResult<()> {
+    fn update_at(&self, collection: &CollectionName, mut document: Document, committed_at: u64) -> DocumentResult<u64> {
         // Check if document exists
         let doc_key = self.document_key(collection, &document.id);
-        if !self.db.contains(&doc_key)? {
+        let Some(stored) = self.get_document(collection, &document.id)? else {
             return Err(DocumentError::DocumentNotFound(document.id.clone()));
-        }
+        };
 
-        // Update metadata
+        // Update metadata, carrying on from the stored version
+        document.metadata = stored.metadata;
         document.metadata.update();
+        self.record_version(collection, &document.id, Some(&document), committed_at)?;
 
         // Store updated document
-        let serialized = self.serialize_document(&document)?;
+        let serialized = self.serialize_document(collection, &document)?;
         self.db.put(doc_key, serialized)?;
+        self.update_covering_indexes(collection, &document.id, Some(&document.content))?;
 
-        Ok(())
+        Ok(document.metadata.version)
     }
 
-    fn delete_document(&self, collection: &CollectionName, id: &DocumentId) -> DocumentResult<bool> {
+    /// Fail unless the document is missing or at `expected_version`, returning whether it exists
+    fn check_version(&self, collection: &CollectionName, id: &DocumentId, expected_version: u64) -> DocumentResult<bool> {
+        match self.get_document(collection, id)? {
+            Some(stored) if stored.metadata.version != expected_version => Err(DocumentError::VersionConflict {
+                id: id.clone(),
+                expected: expected_version,
+                current: stored.metadata.version,
+            }),
+            stored => Ok(stored.is_some()),
+        }
+    }
+
+    fn delete_at(&self, collection: &CollectionName, id: &DocumentId, committed_at: u64) -> DocumentResult<bool> {
         let key = self.document_key(collection, id);
+        if !self.db.contains(&key)? {
+            return Ok(false);
+        }
+        self.record_version(collection, id, None, committed_at)?;
         let existed = self.db.delete(&key)?;
 
         if existed {
             // Remove from collection's document list
             self.remove_from_collection_docs(collection, id)?;
+            self.update_covering_indexes(collection, id, None)?;
         }
 
         Ok(existed)
     }
 
+    fn delete_collection_at(&self, collection: &CollectionName, committed_at: u64) -> DocumentResult<bool> {
+        // Check if collection exists
+        let col_key = self.collection_key(collection);
+        if !self.db.contains(&col_key)? {
+            return Ok(false);
+        }
+
+        // Delete all documents in the collection; their history is kept
+        let doc_ids = self.list_documents(collection)?;
+        for id in doc_ids {
+            self.record_version(collection, &id, None, committed_at)?;
+            let doc_key = self.document_key(collection, &id);
+            self.db.delete(&doc_key)?;
+        }
+
+        // Delete collection document list
+        let docs_key = self.collection_docs_key(collection);
+        self.db.delete(&docs_key)?;
+
+        // Delete covering indexes
+        for paths in self.covering_indexes(collection)? {
+            self.db.delete(&self.covering_index_entries_key(collection, &paths))?;
+        }
+        self.db.delete(&self.covering_indexes_key(collection))?;
+
+        // Delete collection metadata, schema and codec
+        self.db.delete(&col_key)?;
+        self.db.delete(&self.schema_key(collection))?;
+        self.db.delete(&self.compression_key(collection))?;
+
+        // Remove from global collections list
+        self.remove_from_collections_list(collection)?;
+
+        Ok(true)
+    }
+
+    /// Add collection to global collections list
+    fn add_to_collections_list(&self, collection: &CollectionName) -> DocumentResult<()> {
+        let key = self.collections_list_key();
+        let mut collections = if let Some(data) = self.db.get(&key)? {
+            self.deserialize_collection_list(&data)?
+        } else {
+            Vec::new()
+        };
+
+        if !collections.contains(collection) {
+            collections.push(collection.clone());
+            let serialized = self.serialize_collection_list(&collections)?;
+            self.db.put(key, serialized)?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove collection from global collections list
+    fn remove_from_collections_list(&self, collection: &CollectionName) -> DocumentResult<()> {
+        let key = self.collections_list_key();
+        if let Some(data) = self.db.get(&key)? {
+            let mut collections = self.deserialize_collection_list(&data)?;
+            collections.retain(|col| col != collection);
+            let serialized = self.serialize_collection_list(&collections)?;
+            self.db.put(key, serialized)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl DocumentStorage for DocumentStore {
+    fn create_document(&self, collection: &CollectionName, document: Document) -> DocumentResult<DocumentId> {
+        let commit = self.clock.begin();
+        self.create_at(collection, document, commit.timestamp)
+    }
+
+    fn get_document(&self, collection: &CollectionName, id: &DocumentId) -> DocumentResult<Option<Document>> {
+        let key = self.document_key(collection, id);
+        match self.db.get(&key)? {
+            Some(data) => {
+                let document = self.deserialize_document(&data)?;
+                Ok(Some(document))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn get_document_for_scan(&self, collection: &CollectionName, id: &DocumentId) -> DocumentResult<Option<Document>> {
+        let key = self.document_key(collection, id);
+        self.db.get_for_scan(&key)?.map(|data| self.deserialize_document(&data)).transpose()
+    }
+
+    fn update_document(&self, collection: &CollectionName, document: Document) -> DocumentResult<()> {
+        let commit = self.clock.begin();
+        self.update_at(collection, document, commit.timestamp).map(|_| ())
+    }
+
+    fn delete_document(&self, collection: &CollectionName, id: &DocumentId) -> DocumentResult<bool> {
+        let commit = self.clock.begin();
+        self.delete_at(collection, id, commit.timestamp)
+    }
+
+    fn update_document_if_version(&self, collection: &CollectionName, document: Document, expected_version: u64) -> DocumentResult<u64> {
+        let commit = self.clock.begin();
+        if !self.check_version(collection, &document.id, expected_version)? {
+            return Err(DocumentError::DocumentNotFound(document.id.clone()));
+        }
+        self.update_at(collection, document, commit.timestamp)
+    }
+
+    fn delete_document_if_version(&self, collection: &CollectionName, id: &DocumentId, expected_version: u64) -> DocumentResult<bool> {
+        let commit = self.clock.begin();
+        if !self.check_version(collection, id, expected_version)? {
+            return Ok(false);
+        }
+        self.delete_at(collection, id, commit.timestamp)
+    }
+
     fn document_exists(&self, collection: &CollectionName, id: &DocumentId) -> DocumentResult<bool> {
         let key = self.document_key(collection, id);
         Ok(self.db.contains(&key)?)
@@ -264,6 +858,9 @@ impl DocumentStorage for DocumentStore {
     fn create_collection(&self, collection: &CollectionName) -> DocumentResult<()> {
         let key = self.collection_key(collection);
         if !self.db.contains(&key)? {
+            // Namespaces come into existence with their first collection
+            self.create_namespace(&self.namespace)?;
+
             // Create collection metadata
             let metadata = serde_json::json!({
                 "name": collection.as_str(),
@@ -283,30 +880,68 @@ impl DocumentStorage for DocumentStore {
     }
 
     fn delete_collection(&self, collection: &CollectionName) -> DocumentResult<bool> {
-        // Check if collection exists
-        let col_key = self.collection_key(collection);
-        if !self.db.contains(&col_key)? {
-            return Ok(false);
+        let commit = self.clock.begin();
+        self.delete_collection_at(collection, commit.timestamp)
+    }
+
+    fn rename_collection(&self, from: &CollectionName, to: &CollectionName) -> DocumentResult<()> {
+        // Writers wait so that nothing is written under either name while the keys move
+        let _commit = self.clock.begin();
+        let col_key = self.collection_key(from);
+        let Some(metadata) = self.db.get(&col_key)? else {
+            return Err(DocumentError::CollectionNotFound(from.clone()));
+        };
+        if self.db.contains(&self.collection_key(to))? {
+            return Err(DocumentError::CollectionAlreadyExists(to.clone()));
         }
 
-        // Delete all documents in the collection
-        let doc_ids = self.list_documents(collection)?;
-        for id in doc_ids {
-            let doc_key = self.document_key(collection, &id);
-            self.db.delete(&doc_key)?;
+        let mut keys = vec![
+            (self.collection_docs_key(from), self.collection_docs_key(to)),
+            (self.collection_history_key(from), self.collection_history_key(to)),
+            (self.schema_key(from), self.schema_key(to)),
+            (self.compression_key(from), self.compression_key(to)),
+            (self.covering_indexes_key(from), self.covering_indexes_key(to)),
+        ];
+        for paths in self.covering_indexes(from)? {
+            keys.push((self.covering_index_entries_key(from, &paths), self.covering_index_entries_key(to, &paths)));
+        }
+        for id in self.list_documents(from)? {
+            keys.push((self.document_key(from, &id), self.document_key(to, &id)));
+        }
+        if let Some(data) = self.db.get(&self.collection_history_key(from))? {
+            for id in self.deserialize_doc_list(&data)? {
+                keys.push((self.document_versions_key(from, &id), self.document_versions_key(to, &id)));
+            }
         }
 
-        // Delete collection document list
-        let docs_key = self.collection_docs_key(collection);
-        self.db.delete(&docs_key)?;
+        // Everything is in place under the new name before the collection resolves by it
+        for (old, new) in &keys {
+            if let Some(data) = self.db.get(old)? {
+                self.db.put(new.clone(), data)?;
+            }
+        }
+        let mut metadata: Value = serde_json::from_slice(&metadata)?;
+        metadata["name"] = Value::from(to.as_str());
+        self.db.put(self.collection_key(to), serde_json::to_vec(&metadata)?)?;
+
+        // One write moves the collection in the list from the old name to the new one
+        let list_key = self.collections_list_key();
+        let mut collections = match self.db.get(&list_key)? {
+            Some(data) => self.deserialize_collection_list(&data)?,
+            None => Vec::new(),
+        };
+        match collections.iter().position(|collection| collection == from) {
+            Some(position) => collections[position] = to.clone(),
+            None => collections.push(to.clone()),
+        }
+        self.db.put(list_key, self.serialize_collection_list(&collections)?)?;
 
-        // Delete collection metadata
+        // The old name stops resolving before its keys go
         self.db.delete(&col_key)?;
-
-        // Remove from global collections list
-        self.remove_from_collections_list(collection)?;
-
-        Ok(true)
+        for (old, _) in &keys {
+            self.db.delete(old)?;
+        }
+        Ok(())
     }
 
     fn list_collections(&self) -> DocumentResult<Vec<CollectionName>> {
@@ -321,6 +956,303 @@ impl DocumentStorage for DocumentStore {
         let key = self.collection_key(collection);
         Ok(self.db.contains(&key)?)
     }
+
+    fn set_schema(&self, collection: &CollectionName, schema: Option<&Value>) -> DocumentResult<()> {
+        let key = self.schema_key(collection);
+        match schema {
+            Some(schema) => {
+                self.create_collection(collection)?;
+                self.db.put(key, serde_json::to_vec(schema)?)?;
+            }
+            None => {
+                self.db.delete(&key)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn get_schema(&self, collection: &CollectionName) -> DocumentResult<Option<Value>> {
+        match self.db.get(&self.schema_key(collection))? {
+            Some(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn set_compression(&self, collection: &CollectionName, codec: CompressionCodec) -> DocumentResult<()> {
+        let key = self.compression_key(collection);
+        match codec {
+            CompressionCodec::None => {
+                self.db.delete(&key)?;
+            }
+            codec => {
+                self.create_collection(collection)?;
+                self.db.put(key, serde_json::to_vec(&codec)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn get_compression(&self, collection: &CollectionName) -> DocumentResult<CompressionCodec> {
+        match self.db.get(&self.compression_key(collection))? {
+            Some(data) => Ok(serde_json::from_slice(&data)?),
+            None => Ok(CompressionCodec::None),
+        }
+    }
+
+    fn rewrite_documents(&self, collection: &CollectionName, ids: &[DocumentId]) -> DocumentResult<RecompressionReport> {
+        // Writers wait so that no document changes between being read and rewritten
+        let _commit = self.clock.begin();
+        let codec = self.get_compression(collection)?;
+        let mut report = RecompressionReport::default();
+        for id in ids {
+            for key in [self.document_key(collection, id), self.document_versions_key(collection, id)] {
+                let Some(data) = self.db.get(&key)? else {
+                    continue;
+                };
+                let encoded = codec.encode(compression::decode(&data)?.into_owned())?;
+                report.bytes_before += data.len() as u64;
+                report.bytes_after += encoded.len() as u64;
+                if encoded != data {
+                    self.db.put(key, encoded)?;
+                }
+            }
+            report.documents += u64::from(self.db.contains(&self.document_key(collection, id))?);
+        }
+        Ok(report)
+    }
+
+    fn create_covering_index(&self, collection: &CollectionName, paths: &[String]) -> DocumentResult<bool> {
+        let mut indexes = self.covering_indexes(collection)?;
+        if indexes.iter().any(|indexed| indexed == paths) {
+            return Ok(false);
+        }
+
+        let projection = Projection::new(paths)?;
+        let mut entries = Vec::new();
+        for id in self.list_documents(collection)? {
+            if let Some(document) = self.get_document(collection, &id)? {
+                entries.push((id, projection.project(&document.content)));
+            }
+        }
+
+        self.create_collection(collection)?;
+        self.write_covering_index(collection, &CoveringIndex { paths: paths.to_vec(), entries })?;
+        indexes.push(paths.to_vec());
+        self.db.put(self.covering_indexes_key(collection), serde_json::to_vec(&indexes)?)?;
+        Ok(true)
+    }
+
+    fn drop_covering_index(&self, collection: &CollectionName, paths: &[String]) -> DocumentResult<bool> {
+        let mut indexes = self.covering_indexes(collection)?;
+        let before = indexes.len();
+        indexes.retain(|indexed| indexed != paths);
+        if indexes.len() == before {
+            return Ok(false);
+        }
+
+        self.db.delete(&self.covering_index_entries_key(collection, paths))?;
+        self.db.put(self.covering_indexes_key(collection), serde_json::to_vec(&indexes)?)?;
+        Ok(true)
+    }
+
+    fn covering_indexes(&self, collection: &CollectionName) -> DocumentResult<Vec<Vec<String>>> {
+        match self.db.get(&self.covering_indexes_key(collection))? {
+            Some(data) => Ok(serde_json::from_slice(&data)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn read_covering_index(&self, collection: &CollectionName, paths: &[String]) -> DocumentResult<Option<CoveringIndex>> {
+        match self.db.get(&self.covering_index_entries_key(collection, paths))? {
+            Some(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn namespace(&self) -> &Namespace {
+        &self.namespace
+    }
+
+    fn in_namespace(&self, namespace: &Namespace) -> Arc<dyn DocumentStorage> {
+        Arc::new(self.scoped(namespace))
+    }
+
+    fn create_namespace(&self, namespace: &Namespace) -> DocumentResult<bool> {
+        let mut namespaces = self.stored_namespaces()?;
+        if namespaces.contains(namespace) {
+            return Ok(false);
+        }
+
+        namespaces.push(namespace.clone());
+        self.db.put(Self::namespace_key(namespace), Self::namespace_metadata(namespace)?)?;
+        self.db.put(NAMESPACES_KEY.to_vec(), serde_json::to_vec(&namespaces)?)?;
+        Ok(true)
+    }
+
+    fn list_namespaces(&self) -> DocumentResult<Vec<Namespace>> {
+        let mut namespaces = self.stored_namespaces()?;
+        if !namespaces.iter().any(Namespace::is_default) {
+            namespaces.insert(0, Namespace::default());
+        }
+        Ok(namespaces)
+    }
+
+    fn drop_namespace(&self, namespace: &Namespace, force: bool) -> DocumentResult<bool> {
+        if namespace.is_default() {
+            return Err(DocumentError::InvalidNamespace(format!("{} (the default namespace cannot be dropped)", namespace)));
+        }
+
+        let mut namespaces = self.stored_namespaces()?;
+        if !namespaces.contains(namespace) {
+            return Ok(false);
+        }
+
+        let scoped = self.scoped(namespace);
+        let collections = scoped.list_collections()?;
+        if !collections.is_empty() && !force {
+            return Err(DocumentError::NamespaceNotEmpty(namespace.clone()));
+        }
+        let commit = self.clock.begin();
+        for collection in &collections {
+            scoped.delete_collection_at(collection, commit.timestamp)?;
+        }
+        drop(commit);
+
+        self.db.delete(&scoped.collections_list_key())?;
+        self.db.delete(&Self::namespace_key(namespace))?;
+        namespaces.retain(|ns| ns != namespace);
+        self.db.put(NAMESPACES_KEY.to_vec(), serde_json::to_vec(&namespaces)?)?;
+        Ok(true)
+    }
+
+    fn commit_writes(&self, writes: Vec<DocumentWrite>) -> DocumentResult<()> {
+        let commit = self.clock.begin();
+        for write in writes {
+            match write.content {
+                Some(content) => {
+                    let document = Document::with_id(write.id.clone(), content);
+                    if self.document_exists(&write.collection, &write.id)? {
+                        self.update_at(&write.collection, document, commit.timestamp)?;
+                    } else {
+                        self.create_at(&write.collection, document, commit.timestamp)?;
+                    }
+                }
+                None => {
+                    self.delete_at(&write.collection, &write.id, commit.timestamp)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read_snapshot(&self, as_of: u64) -> DocumentResult<u64> {
+        let snapshot = self.clock.snapshot(as_of);
+        let horizon = self.retained_from(generate_timestamp(), self.clock.snapshot(u64::MAX))?;
+        if as_of < horizon {
+            return Err(DocumentError::HistoryUnavailable { requested: as_of, horizon });
+        }
+        Ok(snapshot)
+    }
+
+    fn pin_snapshot(&self, max_age: Duration) -> DocumentResult<SnapshotPin> {
+        Ok(self.clock.pin(max_age))
+    }
+
+    fn get_document_as_of(&self, collection: &CollectionName, id: &DocumentId, snapshot: u64) -> DocumentResult<Option<Document>> {
+        let visible = |versions: &[DocumentVersion]| history::visible_at(versions, snapshot).and_then(|version| version.document.clone());
+        let versions = self.document_versions(collection, id)?;
+        if !versions.is_empty() {
+            return Ok(visible(&versions));
+        }
+
+        // Unwritten since history started, unless a commit landed in between
+        let current = self.get_document(collection, id)?;
+        let versions = self.document_versions(collection, id)?;
+        Ok(if versions.is_empty() { current } else { visible(&versions) })
+    }
+
+    fn list_documents_as_of(&self, collection: &CollectionName, snapshot: u64) -> DocumentResult<Vec<DocumentId>> {
+        let mut ids = match self.db.get(&self.collection_history_key(collection))? {
+            Some(data) => self.deserialize_doc_list(&data)?,
+            None => Vec::new(),
+        };
+        let recorded: HashSet<DocumentId> = ids.iter().cloned().collect();
+        ids.extend(self.list_documents(collection)?.into_iter().filter(|id| !recorded.contains(id)));
+
+        let mut visible = Vec::new();
+        for id in ids {
+            if self.get_document_as_of(collection, &id, snapshot)?.is_some() {
+                visible.push(id);
+            }
+        }
+        Ok(visible)
+    }
+
+    fn space_usage(&self, collection: &CollectionName) -> DocumentResult<CollectionSpaceUsage> {
+        let owned_keys = [
+            self.collection_key(collection),
+            self.collection_docs_key(collection),
+            self.schema_key(collection),
+            self.compression_key(collection),
+            self.covering_indexes_key(collection),
+            self.collection_history_key(collection),
+        ];
+        let owned_prefixes = ["doc", "doc_versions", "idx_entries"].map(|kind| format!("{}:{}:{}:", kind, self.namespace, collection.as_str()).into_bytes());
+        let usage = self
+            .db
+            .space_usage(&|key: &[u8]| owned_keys.iter().any(|owned| owned == key) || owned_prefixes.iter().any(|prefix| key.starts_with(prefix)));
+
+        let mut history_tombstones = 0;
+        if let Some(data) = self.db.get(&self.collection_history_key(collection))? {
+            for id in self.deserialize_doc_list(&data)? {
+                let versions = self.document_versions(collection, &id)?;
+                history_tombstones += u64::from(versions.last().is_some_and(|version| version.document.is_none()));
+            }
+        }
+
+        let ids = self.list_documents(collection)?;
+        let (mut document_bytes, mut stored_document_bytes) = (0, 0);
+        for id in &ids {
+            if let Some(data) = self.db.get(&self.document_key(collection, id))? {
+                document_bytes += compression::uncompressed_len(&data)? as u64;
+                stored_document_bytes += data.len() as u64;
+            }
+        }
+
+        Ok(CollectionSpaceUsage {
+            collection: collection.as_str().to_string(),
+            documents: ids.len() as u64,
+            document_bytes,
+            stored_document_bytes,
+            logical_bytes: usage.live_bytes,
+            on_disk_bytes: usage.live_bytes + usage.dead_bytes,
+            dead_entries: usage.dead_entries,
+            history_tombstones,
+        })
+    }
+
+    fn compact(&self, strategy: &DeadSpaceStrategy) -> DocumentResult<DocumentCompaction> {
+        let mut report = DocumentCompaction::default();
+        {
+            // Writers wait so that no version is recorded while its history is pruned
+            let started = self.clock.snapshot(u64::MAX);
+            let _commit = self.clock.begin();
+            let horizon = self.retained_from(generate_timestamp(), started)?;
+            for namespace in self.list_namespaces()? {
+                let store = self.scoped(&namespace);
+                for collection in store.list_collections()? {
+                    store.prune_history(&collection, horizon, &mut report)?;
+                }
+            }
+        }
+
+        report.segments = self.db.compact(strategy)?;
+        Ok(report)
+    }
+
+    fn flush(&self) -> DocumentResult<()> {
+        Ok(self.db.flush()?)
+    }
 }
 
 #[cfg(test)]
@@ -463,6 +1395,61 @@ mod tests {
         assert!(collections.contains(&collection2));
     }
 
+    #[test]
+    fn test_schema_storage() {
+        let store = create_test_store();
+        let collection = CollectionName::new("users");
+        let schema = serde_json::json!({"type": "object"});
+
+        store.set_schema(&collection, Some(&schema)).unwrap();
+        assert!(store.collection_exists(&collection).unwrap());
+        assert_eq!(store.get_schema(&collection).unwrap(), Some(schema.clone()));
+        assert_eq!(store.in_namespace(&Namespace::new("other").unwrap()).get_schema(&collection).unwrap(), None);
+
+        store.set_schema(&collection, None).unwrap();
+        assert_eq!(store.get_schema(&collection).unwrap(), None);
+
+        // Deleting the collection drops its schema with it
+        store.set_schema(&collection, Some(&schema)).unwrap();
+        store.delete_collection(&collection).unwrap();
+        assert_eq!(store.get_schema(&collection).unwrap(), None);
+    }
+
+    #[test]
+    fn test_covering_index_follows_writes() {
+        let store = create_test_store();
+        let collection = CollectionName::new("users");
+        let paths = vec!["name".to_string(), "address.city".to_string()];
+
+        let alice = Document::new(serde_json::json!({"name": "Alice", "address": {"city": "Oslo"}}));
+        let alice_id = store.create_document(&collection, alice).unwrap();
+        assert!(store.create_covering_index(&collection, &paths).unwrap());
+        assert!(!store.create_covering_index(&collection, &paths).unwrap());
+
+        let bob_id = store.create_document(&collection, Document::new(serde_json::json!({"name": "Bob"}))).unwrap();
+        store
+            .update_document(&collection, Document::with_id(alice_id.clone(), serde_json::json!({"name": "Alice", "address": {"city": "Bergen"}})))
+            .unwrap();
+
+        let index = store.read_covering_index(&collection, &paths).unwrap().unwrap();
+        let present = |value: &str| ProjectedValue::Present(serde_json::json!(value));
+        assert_eq!(
+            index.entries,
+            vec![
+                (alice_id.clone(), vec![present("Alice"), present("Bergen")]),
+                (bob_id.clone(), vec![present("Bob"), ProjectedValue::Missing])
+            ]
+        );
+
+        store.delete_document(&collection, &alice_id).unwrap();
+        assert_eq!(store.read_covering_index(&collection, &paths).unwrap().unwrap().entries.len(), 1);
+
+        // Dropping the collection drops its indexes
+        store.delete_collection(&collection).unwrap();
+        assert!(store.covering_indexes(&collection).unwrap().is_empty());
+        assert!(store.read_covering_index(&collection, &paths).unwrap().is_none());
+    }
+
     #[test]
     fn test_duplicate_document_creation() {
         let store = create_test_store();
@@ -480,6 +1467,97 @@ mod tests {
         assert!(matches!(result, Err(DocumentError::DocumentAlreadyExists(_))));
     }
 
+    #[test]
+    fn test_namespaces_isolate_collections() {
+        let store = create_test_store();
+        let tenant = store.in_namespace(&Namespace::new("tenant_a").unwrap());
+        let collection = CollectionName::new("users");
+
+        let id = tenant.create_document(&collection, Document::new(serde_json::json!({"name": "Alice"}))).unwrap();
+        assert!(tenant.document_exists(&collection, &id).unwrap());
+        assert!(!store.document_exists(&collection, &id).unwrap());
+        assert!(store.list_collections().unwrap().is_empty());
+        assert_eq!(tenant.list_collections().unwrap(), vec![collection.clone()]);
+
+        let namespaces = store.list_namespaces().unwrap();
+        assert_eq!(namespaces, vec![Namespace::default(), Namespace::new("tenant_a").unwrap()]);
+    }
+
+    #[test]
+    fn test_drop_namespace_requires_force_when_not_empty() {
+        let store = create_test_store();
+        let namespace = Namespace::new("tenant_b").unwrap();
+        let tenant = store.in_namespace(&namespace);
+        let collection = CollectionName::new("orders");
+        let id = tenant.create_document(&collection, Document::new(serde_json::json!({"total": 3}))).unwrap();
+
+        assert!(matches!(store.drop_namespace(&namespace, false), Err(DocumentError::NamespaceNotEmpty(_))));
+        assert!(tenant.document_exists(&collection, &id).unwrap());
+
+        assert!(store.drop_namespace(&namespace, true).unwrap());
+        assert!(!tenant.document_exists(&collection, &id).unwrap());
+        assert!(!store.list_namespaces().unwrap().contains(&namespace));
+        assert!(!store.drop_namespace(&namespace, false).unwrap());
+        assert!(store.drop_namespace(&Namespace::default(), true).is_err());
+
+        // An empty namespace can be dropped without force
+        assert!(store.create_namespace(&namespace).unwrap());
+        assert!(store.drop_namespace(&namespace, false).unwrap());
+    }
+
+    #[test]
+    fn test_open_migrates_legacy_layout() {
+        let db: Arc<dyn DatabaseInterface> = Arc::new(Database::new_in_memory().unwrap());
+        let document = Document::new(serde_json::json!({"name": "Legacy"}));
+        let id = document.id.clone();
+        db.put(b"collections".to_vec(), serde_json::to_vec(&[CollectionName::new("users")]).unwrap()).unwrap();
+        db.put(b"col:users".to_vec(), b"{}".to_vec()).unwrap();
+        db.put(b"col_docs:users".to_vec(), serde_json::to_vec(&[id.clone()]).unwrap()).unwrap();
+        db.put(format!("doc:users:{id}").into_bytes(), serde_json::to_vec(&document).unwrap()).unwrap();
+
+        let store = DocumentStore::open(db.clone()).unwrap();
+        let collection = CollectionName::new("users");
+        assert_eq!(store.list_collections().unwrap(), vec![collection.clone()]);
+        assert_eq!(store.list_documents(&collection).unwrap(), vec![id.clone()]);
+        assert_eq!(store.get_document(&collection, &id).unwrap().unwrap().content, document.content);
+        assert!(!db.contains(b"collections").unwrap());
+        assert!(!db.contains(format!("doc:users:{id}").as_bytes()).unwrap());
+
+        // Opening again leaves the migrated data alone
+        let store = DocumentStore::open(db).unwrap();
+        assert_eq!(store.list_documents(&collection).unwrap(), vec![id]);
+    }
+
+    #[test]
+    fn test_compressed_documents_and_histories() {
+        let db: Arc<dyn DatabaseInterface> = Arc::new(Database::new_in_memory().unwrap());
+        let store = DocumentStore::open(db.clone()).unwrap();
+        let collection = CollectionName::new("logs");
+        let content = |n: u64| serde_json::json!({"level": "info", "message": "request served ".repeat(10), "n": n});
+
+        store.set_compression(&collection, CompressionCodec::Lz4).unwrap();
+        let id = store.create_document(&collection, Document::new(content(1))).unwrap();
+        let snapshot = store.read_snapshot(u64::MAX).unwrap();
+        let mut updated = store.get_document(&collection, &id).unwrap().unwrap();
+        updated.content = content(2);
+        store.update_document(&collection, updated).unwrap();
+
+        // Both the document and its versions are stored compressed
+        for key in [store.document_key(&collection, &id), store.document_versions_key(&collection, &id)] {
+            assert_eq!(db.get(&key).unwrap().unwrap()[0], 0);
+        }
+        assert_eq!(store.get_document(&collection, &id).unwrap().unwrap().content, content(2));
+        assert_eq!(store.get_document_as_of(&collection, &id, snapshot).unwrap().unwrap().content, content(1));
+
+        let report = store.rewrite_documents(&collection, &[id.clone(), DocumentId::new()]).unwrap();
+        assert_eq!(report.documents, 1);
+        assert_eq!(report.bytes_before, report.bytes_after);
+
+        // Dropping the collection forgets its codec
+        assert!(store.delete_collection(&collection).unwrap());
+        assert_eq!(store.get_compression(&collection).unwrap(), CompressionCodec::None);
+    }
+
     #[test]
     fn test_update_nonexistent_document() {
         let store = create_test_store();
@@ -492,4 +1570,71 @@ mod tests {
         let result = store.update_document(&collection, document);
         assert!(matches!(result, Err(DocumentError::DocumentNotFound(_))));
     }
+
+    #[test]
+    fn test_compaction_reclaims_deleted_documents() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = DbConfig {
+            segment_size: 16 * 1024,
+            ..DbConfig::default()
+        };
+        let db = Arc::new(Database::new(temp_dir.path(), config).unwrap());
+        let store = DocumentStore::open(db.clone()).unwrap().with_history_retention(Duration::from_secs(3600));
+        let users = CollectionName::new("users");
+        let on_disk = || -> u64 {
+            std::fs::read_dir(temp_dir.path())
+                .unwrap()
+                .map(|entry| entry.unwrap())
+                .filter(|entry| entry.file_name().to_string_lossy().starts_with("data"))
+                .map(|entry| entry.metadata().unwrap().len())
+                .sum()
+        };
+
+        let ids: Vec<DocumentId> = (0..200)
+            .map(|i| {
+                let content = serde_json::json!({"name": format!("user_{i}"), "bio": "x".repeat(200)});
+                store.create_document(&users, Document::new(content)).unwrap()
+            })
+            .collect();
+        // Rewriting the document list on every insert leaves garbage of its own
+        let strategy = DeadSpaceStrategy {
+            max_segments: usize::MAX,
+            ..DeadSpaceStrategy::default()
+        };
+        let compact_all = DeadSpaceStrategy {
+            dead_ratio_threshold: 0.0,
+            ..strategy.clone()
+        };
+        store.compact(&compact_all).unwrap();
+        let before = on_disk();
+        let usage_before = store.space_usage(&users).unwrap();
+        assert_eq!(usage_before.documents, 200);
+        assert_eq!(usage_before.on_disk_bytes, usage_before.logical_bytes);
+
+        for id in &ids[..180] {
+            store.delete_document(&users, id).unwrap();
+        }
+        let usage = store.space_usage(&users).unwrap();
+        assert_eq!((usage.documents, usage.history_tombstones), (20, 180));
+        assert!(usage.space_amplification() > 1.5);
+
+        // Reads as of a time before the deletes can still see the documents, so
+        // compaction keeps their tombstones until the retention window passes
+        let kept = store.compact(&strategy).unwrap();
+        assert_eq!(kept.tombstones_dropped, 0);
+
+        // Reopening with a shorter retention puts the deletes outside the window
+        let store = DocumentStore::open(db).unwrap().with_history_retention(Duration::ZERO);
+        let report = store.compact(&strategy).unwrap();
+        assert_eq!(report.tombstones_dropped, 180);
+        assert!(report.segments.segments_compacted > 0);
+        let after = on_disk();
+        assert!(after * 5 < before, "{after} bytes on disk after compaction, {before} before the deletes");
+
+        let usage = store.space_usage(&users).unwrap();
+        assert_eq!((usage.documents, usage.history_tombstones), (20, 0));
+        assert!(usage.logical_bytes * 5 < usage_before.logical_bytes);
+        assert_eq!(store.count_documents(&users).unwrap(), 20);
+        assert!(store.get_document(&users, &ids[199]).unwrap().is_some());
+    }
 }
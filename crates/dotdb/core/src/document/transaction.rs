@@ -0,0 +1,503 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Document Transactions
+//!
+//! Groups document reads and writes into a transaction run at one of the storage
+//! engine's isolation levels. Writes are buffered in the transaction and applied when it
+//! commits. Documents are locked through the storage engine's lock manager without
+//! waiting: a transaction that would block on another (and so could deadlock) is aborted
+//! instead, with an error the caller can retry on.
+//!
+//! What each level guarantees:
+//!
+//! - `ReadUncommitted`: reads see writes other transactions have not committed yet.
+//! - `ReadCommitted`: reads only see committed documents, but reading the same document
+//!   twice may return different versions.
+//! - `RepeatableRead`: the first version read of each document is kept for the rest of
+//!   the transaction, and committing an update to a document that changed since it was
+//!   read fails. Two transactions that each read a pair of documents and update a
+//!   different one can both commit (write skew).
+//! - `Serializable`: reads take shared locks held until commit, so writing a document
+//!   another open transaction has read aborts. This rules out write skew.
+//!
+//! Documents are checked against their collection's schema when they are written to the
+//! transaction, so a schema violation fails that write without aborting the transaction.
+//!
+//! Writes made through [`CollectionManager`](super::CollectionManager) outside a
+//! transaction take no locks. Committed writes are applied one document at a time, so a
+//! storage failure during commit leaves the documents written before it in place.
+
+use super::{CollectionName, DocumentError, DocumentId, DocumentResult, DocumentStorage, DocumentWrite, JsonSchema};
+use crate::storage_engine::transaction::TransactionId;
+use crate::storage_engine::{IsolationLevel, LockManager, LockType, PageId};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// A document addressed by collection and ID
+type DocumentKey = (CollectionName, DocumentId);
+
+/// Why a transaction was aborted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AbortReason {
+    /// Another transaction holds a conflicting lock on the document
+    LockConflict { collection: CollectionName, id: DocumentId },
+    /// The document was changed by another transaction after this one read it
+    WriteConflict { collection: CollectionName, id: DocumentId },
+}
+
+impl fmt::Display for AbortReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LockConflict { collection, id } => write!(f, "document {collection}/{id} is locked by another transaction"),
+            Self::WriteConflict { collection, id } => write!(f, "document {collection}/{id} was modified after it was read"),
+        }
+    }
+}
+
+/// State shared by every transaction of one collection manager
+pub(crate) struct TransactionRegistry {
+    lock_manager: LockManager,
+    next_id: AtomicU64,
+    /// Uncommitted writes by document, visible to `ReadUncommitted` readers. Writers hold
+    /// an exclusive lock, so each document has at most one pending write.
+    uncommitted: RwLock<HashMap<DocumentKey, (TransactionId, Option<Value>)>>,
+    /// Serializes commit validation and application
+    commit_lock: Mutex<()>,
+}
+
+impl TransactionRegistry {
+    pub(crate) fn new() -> Self {
+        Self {
+            lock_manager: LockManager::new(),
+            next_id: AtomicU64::new(1),
+            uncommitted: RwLock::new(HashMap::new()),
+            commit_lock: Mutex::new(()),
+        }
+    }
+}
+
+/// A transaction over the documents of a collection manager
+///
+/// Dropping a transaction without committing rolls it back. A transaction shares the
+/// manager's storage rather than borrowing the manager, so it can be kept open across
+/// requests and moved between threads.
+pub struct DocumentTransaction {
+    storage: Arc<dyn DocumentStorage>,
+    registry: Arc<TransactionRegistry>,
+    id: TransactionId,
+    isolation_level: IsolationLevel,
+    /// First committed version read of each document; `None` if it did not exist
+    snapshot: HashMap<DocumentKey, Option<Value>>,
+    /// Buffered writes; `None` deletes the document
+    writes: HashMap<DocumentKey, Option<Value>>,
+    /// Order documents were first written in, used when applying the writes
+    write_order: Vec<DocumentKey>,
+    aborted: Option<AbortReason>,
+    finished: bool,
+}
+
+impl DocumentTransaction {
+    pub(crate) fn begin(storage: Arc<dyn DocumentStorage>, registry: Arc<TransactionRegistry>, isolation_level: IsolationLevel) -> Self {
+        Self {
+            id: registry.next_id.fetch_add(1, Ordering::SeqCst),
+            storage,
+            registry,
+            isolation_level,
+            snapshot: HashMap::new(),
+            writes: HashMap::new(),
+            write_order: Vec::new(),
+            aborted: None,
+            finished: false,
+        }
+    }
+
+    /// Get the transaction ID
+    pub fn id(&self) -> TransactionId {
+        self.id
+    }
+
+    /// Get the isolation level the transaction runs at
+    pub fn isolation_level(&self) -> IsolationLevel {
+        self.isolation_level
+    }
+
+    /// Insert a JSON value into a collection
+    pub fn insert_value(&mut self, collection: &str, value: Value) -> DocumentResult<DocumentId> {
+        self.ensure_active()?;
+        let collection = CollectionName::new(collection);
+        self.check_schema(&collection, &value)?;
+        let id = DocumentId::new();
+        self.write((collection, id.clone()), Some(value))?;
+        Ok(id)
+    }
+
+    /// Insert a JSON document into a collection
+    pub fn insert_json(&mut self, collection: &str, json: &str) -> DocumentResult<DocumentId> {
+        self.insert_value(collection, serde_json::from_str(json)?)
+    }
+
+    /// Get a document as JSON value
+    pub fn get_value(&mut self, collection: &str, id: &DocumentId) -> DocumentResult<Option<Value>> {
+        self.read(&(CollectionName::new(collection), id.clone()))
+    }
+
+    /// Get a document as JSON string
+    pub fn get_json(&mut self, collection: &str, id: &DocumentId) -> DocumentResult<Option<String>> {
+        match self.get_value(collection, id)? {
+            Some(value) => Ok(Some(serde_json::to_string(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Replace a document with a JSON value
+    pub fn update_value(&mut self, collection: &str, id: &DocumentId, value: Value) -> DocumentResult<()> {
+        let key = (CollectionName::new(collection), id.clone());
+        if self.read(&key)?.is_none() {
+            return Err(DocumentError::DocumentNotFound(id.clone()));
+        }
+        self.check_schema(&key.0, &value)?;
+        self.write(key, Some(value))
+    }
+
+    /// Replace a document with a JSON string
+    pub fn update_json(&mut self, collection: &str, id: &DocumentId, json: &str) -> DocumentResult<()> {
+        self.update_value(collection, id, serde_json::from_str(json)?)
+    }
+
+    /// Delete a document, returning whether it existed
+    pub fn delete(&mut self, collection: &str, id: &DocumentId) -> DocumentResult<bool> {
+        let key = (CollectionName::new(collection), id.clone());
+        if self.read(&key)?.is_none() {
+            return Ok(false);
+        }
+        self.write(key, None)?;
+        Ok(true)
+    }
+
+    /// Reject values that do not match the collection's schema
+    fn check_schema(&self, collection: &CollectionName, value: &Value) -> DocumentResult<()> {
+        match self.storage.get_schema(collection)? {
+            Some(schema) => JsonSchema::compile(&schema)?.check_document(collection, value),
+            None => Ok(()),
+        }
+    }
+
+    /// Validate and apply the buffered writes
+    pub fn commit(mut self) -> DocumentResult<()> {
+        self.ensure_active()?;
+        let registry = self.registry.clone();
+        let _commit = registry.commit_lock.lock().unwrap();
+
+        // A document updated after being read must still hold the version that was read,
+        // otherwise another transaction's update would be lost
+        if matches!(self.isolation_level, IsolationLevel::RepeatableRead | IsolationLevel::Serializable) {
+            let mut changed = None;
+            for key in &self.write_order {
+                if let Some(seen) = self.snapshot.get(key)
+                    && self.read_committed(key)? != *seen
+                {
+                    changed = Some(key.clone());
+                    break;
+                }
+            }
+            if let Some((collection, id)) = changed {
+                return Err(self.abort(AbortReason::WriteConflict { collection, id }));
+            }
+        }
+
+        let result = self.apply();
+        self.finish();
+        result
+    }
+
+    /// Discard the buffered writes and release the transaction's locks
+    pub fn rollback(mut self) {
+        self.finish();
+    }
+
+    fn apply(&self) -> DocumentResult<()> {
+        let writes = self
+            .write_order
+            .iter()
+            .map(|key| DocumentWrite {
+                collection: key.0.clone(),
+                id: key.1.clone(),
+                content: self.writes[key].clone(),
+            })
+            .collect();
+        self.storage.commit_writes(writes)
+    }
+
+    fn read(&mut self, key: &DocumentKey) -> DocumentResult<Option<Value>> {
+        self.ensure_active()?;
+        if let Some(value) = self.writes.get(key) {
+            return Ok(value.clone());
+        }
+
+        match self.isolation_level {
+            IsolationLevel::ReadUncommitted => {
+                if let Some((owner, value)) = self.registry.uncommitted.read().unwrap().get(key)
+                    && *owner != self.id
+                {
+                    return Ok(value.clone());
+                }
+                self.read_committed(key)
+            }
+            IsolationLevel::ReadCommitted => self.read_committed(key),
+            IsolationLevel::RepeatableRead => self.read_snapshot(key),
+            IsolationLevel::Serializable => {
+                self.lock(key, LockType::Shared)?;
+                self.read_snapshot(key)
+            }
+        }
+    }
+
+    fn read_snapshot(&mut self, key: &DocumentKey) -> DocumentResult<Option<Value>> {
+        if let Some(value) = self.snapshot.get(key) {
+            return Ok(value.clone());
+        }
+        let value = self.read_committed(key)?;
+        self.snapshot.insert(key.clone(), value.clone());
+        Ok(value)
+    }
+
+    fn read_committed(&self, key: &DocumentKey) -> DocumentResult<Option<Value>> {
+        Ok(self.storage.get_document(&key.0, &key.1)?.map(|document| document.content))
+    }
+
+    fn write(&mut self, key: DocumentKey, value: Option<Value>) -> DocumentResult<()> {
+        self.lock(&key, LockType::Exclusive)?;
+        self.registry.uncommitted.write().unwrap().insert(key.clone(), (self.id, value.clone()));
+        if self.writes.insert(key.clone(), value).is_none() {
+            self.write_order.push(key);
+        }
+        Ok(())
+    }
+
+    fn lock(&mut self, key: &DocumentKey, lock_type: LockType) -> DocumentResult<()> {
+        if self.registry.lock_manager.try_lock(self.id, lock_resource(key), lock_type)? {
+            Ok(())
+        } else {
+            Err(self.abort(AbortReason::LockConflict {
+                collection: key.0.clone(),
+                id: key.1.clone(),
+            }))
+        }
+    }
+
+    fn ensure_active(&self) -> DocumentResult<()> {
+        match &self.aborted {
+            Some(reason) => Err(DocumentError::TransactionAborted {
+                txn_id: self.id,
+                reason: reason.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Roll back and remember why, returning the error to report
+    fn abort(&mut self, reason: AbortReason) -> DocumentError {
+        self.finish();
+        self.aborted = Some(reason.clone());
+        DocumentError::TransactionAborted { txn_id: self.id, reason }
+    }
+
+    /// Drop pending writes and release locks
+    fn finish(&mut self) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        self.registry.uncommitted.write().unwrap().retain(|_, (owner, _)| *owner != self.id);
+        let _ = self.registry.lock_manager.release_transaction_locks(self.id);
+    }
+}
+
+impl Drop for DocumentTransaction {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Lock manager resource for a document. Hash collisions only cause spurious conflicts.
+fn lock_resource((collection, id): &DocumentKey) -> PageId {
+    let mut hasher = DefaultHasher::new();
+    collection.hash(&mut hasher);
+    id.hash(&mut hasher);
+    PageId(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::{CollectionManager, create_in_memory_collection_manager};
+    use crate::storage_engine::IsolationLevel;
+    use serde_json::json;
+
+    fn create_test_manager() -> CollectionManager {
+        create_in_memory_collection_manager().unwrap()
+    }
+
+    #[test]
+    fn test_commit_and_rollback() {
+        let manager = create_test_manager();
+
+        let mut txn = manager.begin_transaction(IsolationLevel::ReadCommitted);
+        let kept = txn.insert_value("users", json!({"name": "Alice"})).unwrap();
+        assert_eq!(txn.get_value("users", &kept).unwrap(), Some(json!({"name": "Alice"})));
+        assert_eq!(manager.get_value("users", &kept).unwrap(), None);
+        txn.commit().unwrap();
+        assert_eq!(manager.get_value("users", &kept).unwrap(), Some(json!({"name": "Alice"})));
+
+        let mut txn = manager.begin_transaction(IsolationLevel::ReadCommitted);
+        txn.update_value("users", &kept, json!({"name": "Bob"})).unwrap();
+        let discarded = txn.insert_value("users", json!({"name": "Carol"})).unwrap();
+        txn.rollback();
+        assert_eq!(manager.get_value("users", &kept).unwrap(), Some(json!({"name": "Alice"})));
+        assert_eq!(manager.get_value("users", &discarded).unwrap(), None);
+
+        let mut txn = manager.begin_transaction(IsolationLevel::Serializable);
+        assert!(txn.delete("users", &kept).unwrap());
+        txn.commit().unwrap();
+        assert!(!manager.exists("users", &kept).unwrap());
+    }
+
+    #[test]
+    fn test_writes_are_checked_against_the_schema() {
+        let manager = create_test_manager();
+        manager.set_schema("users", r#"{"required": ["name"]}"#, false).unwrap();
+
+        let mut txn = manager.begin_transaction(IsolationLevel::ReadCommitted);
+        let id = txn.insert_value("users", json!({"name": "Alice"})).unwrap();
+        assert!(txn.insert_value("users", json!({})).is_err());
+        assert!(txn.update_value("users", &id, json!({"age": 3})).is_err());
+        txn.commit().unwrap();
+        assert_eq!(manager.count("users").unwrap(), 1);
+    }
+
+    #[test]
+    fn test_dirty_reads() {
+        let manager = create_test_manager();
+        let id = manager.insert_value("accounts", json!({"balance": 100})).unwrap();
+
+        let mut writer = manager.begin_transaction(IsolationLevel::ReadCommitted);
+        writer.update_value("accounts", &id, json!({"balance": 0})).unwrap();
+
+        let mut uncommitted = manager.begin_transaction(IsolationLevel::ReadUncommitted);
+        assert_eq!(uncommitted.get_value("accounts", &id).unwrap(), Some(json!({"balance": 0})));
+
+        let mut committed = manager.begin_transaction(IsolationLevel::ReadCommitted);
+        assert_eq!(committed.get_value("accounts", &id).unwrap(), Some(json!({"balance": 100})));
+
+        writer.rollback();
+        assert_eq!(uncommitted.get_value("accounts", &id).unwrap(), Some(json!({"balance": 100})));
+    }
+
+    #[test]
+    fn test_repeatable_reads() {
+        let manager = create_test_manager();
+        let id = manager.insert_value("accounts", json!({"balance": 100})).unwrap();
+
+        let mut read_committed = manager.begin_transaction(IsolationLevel::ReadCommitted);
+        let mut repeatable = manager.begin_transaction(IsolationLevel::RepeatableRead);
+        assert_eq!(read_committed.get_value("accounts", &id).unwrap(), Some(json!({"balance": 100})));
+        assert_eq!(repeatable.get_value("accounts", &id).unwrap(), Some(json!({"balance": 100})));
+
+        manager.update_value("accounts", &id, json!({"balance": 50})).unwrap();
+
+        assert_eq!(read_committed.get_value("accounts", &id).unwrap(), Some(json!({"balance": 50})));
+        assert_eq!(repeatable.get_value("accounts", &id).unwrap(), Some(json!({"balance": 100})));
+    }
+
+    #[test]
+    fn test_write_locks_conflict() {
+        let manager = create_test_manager();
+        let id = manager.insert_value("accounts", json!({"balance": 100})).unwrap();
+
+        let mut first = manager.begin_transaction(IsolationLevel::ReadCommitted);
+        let mut second = manager.begin_transaction(IsolationLevel::ReadCommitted);
+        first.update_value("accounts", &id, json!({"balance": 90})).unwrap();
+
+        let err = second.update_value("accounts", &id, json!({"balance": 80})).unwrap_err();
+        assert!(err.is_retryable());
+        // The aborted transaction refuses further work
+        assert!(second.get_value("accounts", &id).unwrap_err().is_retryable());
+        assert!(second.commit().is_err());
+
+        first.commit().unwrap();
+        assert_eq!(manager.get_value("accounts", &id).unwrap(), Some(json!({"balance": 90})));
+    }
+
+    #[test]
+    fn test_lost_update_detected_under_repeatable_read() {
+        let manager = create_test_manager();
+        let id = manager.insert_value("counters", json!({"value": 1})).unwrap();
+
+        let mut txn = manager.begin_transaction(IsolationLevel::RepeatableRead);
+        assert_eq!(txn.get_value("counters", &id).unwrap(), Some(json!({"value": 1})));
+        manager.update_value("counters", &id, json!({"value": 5})).unwrap();
+        txn.update_value("counters", &id, json!({"value": 2})).unwrap();
+
+        let err = txn.commit().unwrap_err();
+        assert!(err.is_retryable());
+        assert_eq!(manager.get_value("counters", &id).unwrap(), Some(json!({"value": 5})));
+    }
+
+    /// Two doctors are on call and each goes off call if the other still is. Run
+    /// concurrently, both see the other on call and nobody is left.
+    fn go_off_call(manager: &CollectionManager, level: IsolationLevel) -> (crate::document::DocumentResult<()>, crate::document::DocumentResult<()>) {
+        let alice = manager.insert_value("doctors", json!({"on_call": true})).unwrap();
+        let bob = manager.insert_value("doctors", json!({"on_call": true})).unwrap();
+
+        let mut first = manager.begin_transaction(level);
+        let mut second = manager.begin_transaction(level);
+        for txn in [&mut first, &mut second] {
+            assert_eq!(txn.get_value("doctors", &alice).unwrap(), Some(json!({"on_call": true})));
+            assert_eq!(txn.get_value("doctors", &bob).unwrap(), Some(json!({"on_call": true})));
+        }
+
+        let first_result = first.update_value("doctors", &alice, json!({"on_call": false})).and_then(|_| first.commit());
+        let second_result = second.update_value("doctors", &bob, json!({"on_call": false})).and_then(|_| second.commit());
+        (first_result, second_result)
+    }
+
+    #[test]
+    fn test_write_skew_allowed_under_repeatable_read() {
+        let manager = create_test_manager();
+        let (first, second) = go_off_call(&manager, IsolationLevel::RepeatableRead);
+        assert!(first.is_ok());
+        assert!(second.is_ok());
+
+        let on_call = manager.find_by_field("doctors", "on_call", &json!(true)).unwrap();
+        assert!(on_call.is_empty());
+    }
+
+    #[test]
+    fn test_write_skew_prevented_under_serializable() {
+        let manager = create_test_manager();
+        let (first, second) = go_off_call(&manager, IsolationLevel::Serializable);
+        assert!(first.unwrap_err().is_retryable());
+        assert!(second.is_ok());
+
+        let on_call = manager.find_by_field("doctors", "on_call", &json!(true)).unwrap();
+        assert_eq!(on_call.len(), 1);
+    }
+}
@@ -26,6 +26,9 @@ const DEFAULT_ORDER: usize = 100;
 /// Minimum order for B+ tree
 const MIN_ORDER: usize = 3;
 
+/// Default fraction of node capacity filled by bulk loading
+pub const DEFAULT_FILL_FACTOR: f64 = 0.67;
+
 /// Node types in B+ tree
 #[derive(Debug, Clone, PartialEq)]
 pub enum NodeType {
@@ -137,8 +140,8 @@ where
             BPlusTreeNode::new_internal(self.max_keys + 1)
         };
 
-        // Move keys to new node
-        new_node.keys = self.keys.split_off(mid);
+        // Move keys to new node; internal nodes keep the middle key so the caller can promote it
+        new_node.keys = if self.is_leaf() { self.keys.split_off(mid) } else { self.keys.split_off(mid + 1) };
 
         if self.is_leaf() {
             // For leaf nodes, move values and update leaf pointers
@@ -180,7 +183,11 @@ where
             return None;
         }
 
-        let pos = self.keys.binary_search(key).unwrap_or_else(|e| e);
+        // A separator is the smallest key of its right subtree, so equal keys go right
+        let pos = match self.keys.binary_search(key) {
+            Ok(pos) => pos + 1,
+            Err(pos) => pos,
+        };
         self.children.get(pos).cloned()
     }
 }
@@ -197,6 +204,8 @@ where
     order: usize,
     /// Number of entries in the tree
     size: usize,
+    /// Fraction of node capacity filled by bulk loading
+    fill_factor: f64,
     /// Enable prefix compression for keys
     compression_enabled: bool,
     /// Compression statistics
@@ -269,6 +278,7 @@ where
             root: None,
             order,
             size: 0,
+            fill_factor: DEFAULT_FILL_FACTOR,
             compression_enabled: false,
             compression_stats: CompressionStats::new(),
             prefix_cache: HashMap::new(),
@@ -395,94 +405,137 @@ where
         &self.compression_stats
     }
 
-    /// Bulk load data into the B+ tree for optimal performance
+    /// Build a tree of the given order from unsorted entries using the bulk loader.
+    /// `fill_factor` (in `(0, 1]`) is the fraction of each node filled; lower values
+    /// leave room for future inserts at the cost of more nodes.
+    pub fn from_entries(order: usize, fill_factor: f64, entries: Vec<(K, V)>) -> IndexResult<Self> {
+        let mut tree = Self::with_order(order);
+        tree.set_fill_factor(fill_factor)?;
+        tree.bulk_load(entries)?;
+        Ok(tree)
+    }
+
+    /// Get the fill factor used by bulk loading
+    pub fn fill_factor(&self) -> f64 {
+        self.fill_factor
+    }
+
+    /// Set the fill factor used by bulk loading, in `(0, 1]`
+    pub fn set_fill_factor(&mut self, fill_factor: f64) -> IndexResult<()> {
+        if !(fill_factor > 0.0 && fill_factor <= 1.0) {
+            return Err(IndexError::InvalidOperation(format!("Fill factor must be in (0, 1], got {fill_factor}")));
+        }
+        self.fill_factor = fill_factor;
+        Ok(())
+    }
+
+    /// Replace the tree contents by building it bottom-up: entries are sorted, packed
+    /// into linked leaves at the configured fill factor, then internal levels are built
+    /// over them. No node is ever split, which makes this far cheaper than repeated inserts.
+    ///
+    /// Duplicate keys are rejected like `insert` does, and the tree is left untouched.
     pub fn bulk_load(&mut self, mut data: Vec<(K, V)>) -> IndexResult<()> {
-        // Sort data by key for optimal loading
         data.sort_by(|a, b| a.0.cmp(&b.0));
+        if data.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            return Err(IndexError::InvalidOperation("Duplicate key not allowed".to_string()));
+        }
 
-        // Clear existing tree
         self.clear();
-
-        // Build tree bottom-up for optimal structure
         if data.is_empty() {
             return Ok(());
         }
-
-        // Calculate optimal leaf size
-        let leaf_size = (self.order * 2) / 3; // Fill factor of ~67%
-        let mut leaves = Vec::new();
-        let mut current_leaf = BPlusTreeNode::new_leaf(self.order);
-
-        for (key, value) in data {
-            if current_leaf.keys.len() >= leaf_size {
-                // Create new leaf and link it
-                let leaf_arc = Arc::new(RwLock::new(current_leaf));
-                leaves.push(leaf_arc);
-                current_leaf = BPlusTreeNode::new_leaf(self.order);
-            }
-
-            current_leaf.keys.push(key);
-            current_leaf.values.push(value);
-            self.size += 1;
+        self.size = data.len();
+
+        // Inserts split a node as soon as it reaches `max_keys`, so a leaf holds at most
+        // `order - 2` keys and an internal node at most `order - 1` children at rest
+        let leaf_capacity = (self.order - 2).max(1);
+        let leaf_count = data.len().div_ceil(self.fill_target(leaf_capacity));
+
+        let mut entries = data.into_iter();
+        let mut level = Vec::with_capacity(leaf_count);
+        for size in even_chunks(self.size, leaf_count) {
+            let mut leaf = BPlusTreeNode::new_leaf(self.order);
+            (leaf.keys, leaf.values) = entries.by_ref().take(size).unzip();
+            let min_key = leaf.keys[0].clone();
+            level.push((min_key, Arc::new(RwLock::new(leaf))));
         }
 
-        // Add the last leaf
-        if !current_leaf.keys.is_empty() {
-            leaves.push(Arc::new(RwLock::new(current_leaf)));
+        for pair in level.windows(2) {
+            pair[0].1.write().unwrap().next_leaf = Some(pair[1].1.clone());
+            pair[1].1.write().unwrap().prev_leaf = Some(pair[0].1.clone());
         }
 
-        // Link leaves together
-        for i in 0..leaves.len() {
-            if i > 0 {
-                leaves[i].write().unwrap().prev_leaf = Some(leaves[i - 1].clone());
-            }
-            if i < leaves.len() - 1 {
-                leaves[i].write().unwrap().next_leaf = Some(leaves[i + 1].clone());
+        let fanout = self.fill_target(self.order - 1);
+        while level.len() > 1 {
+            // Every internal node needs at least two children
+            let node_count = level.len().div_ceil(fanout).min(level.len() / 2).max(1);
+            let mut next_level = Vec::with_capacity(node_count);
+            let mut children = level.into_iter();
+
+            for size in even_chunks(children.len(), node_count) {
+                let group: Vec<_> = children.by_ref().take(size).collect();
+                let min_key = group[0].0.clone();
+
+                // Separator i is the smallest key under child i + 1
+                let mut node = BPlusTreeNode::new_internal(self.order);
+                node.keys = group[1..].iter().map(|(key, _)| key.clone()).collect();
+                node.children = group.into_iter().map(|(_, child)| child).collect();
+
+                let node = Arc::new(RwLock::new(node));
+                for child in &node.read().unwrap().children {
+                    child.write().unwrap().parent = Some(node.clone());
+                }
+                next_level.push((min_key, node));
             }
-        }
-
-        // Build internal nodes bottom-up
-        let mut current_level = leaves;
 
-        while current_level.len() > 1 {
-            let mut next_level = Vec::new();
-            let mut current_internal = BPlusTreeNode::new_internal(self.order);
-            let internal_size = self.order - 1; // Fill factor for internal nodes
+            level = next_level;
+        }
 
-            for (i, child) in current_level.iter().enumerate() {
-                if current_internal.children.len() >= internal_size && i < current_level.len() - 1 {
-                    // Create new internal node
-                    let internal_arc = Arc::new(RwLock::new(current_internal));
-                    next_level.push(internal_arc);
-                    current_internal = BPlusTreeNode::new_internal(self.order);
-                }
+        self.root = level.pop().map(|(_, root)| root);
+        Ok(())
+    }
 
-                current_internal.children.push(child.clone());
+    /// Number of slots to fill out of `capacity` at the configured fill factor
+    fn fill_target(&self, capacity: usize) -> usize {
+        ((capacity as f64 * self.fill_factor).ceil() as usize).clamp(1, capacity)
+    }
 
-                // Set parent pointer
-                child.write().unwrap().parent = Some(Arc::new(RwLock::new(current_internal.clone())));
+    /// Check ordering, key bounds, fan-out and uniform leaf depth below `node_arc`,
+    /// collecting its leaves left to right. `lower` is inclusive, `upper` exclusive.
+    fn verify_node(
+        node_arc: &Arc<RwLock<BPlusTreeNode<K, V>>>,
+        lower: Option<&K>,
+        upper: Option<&K>,
+        depth: usize,
+        leaf_depth: &mut Option<usize>,
+        leaves: &mut Vec<Arc<RwLock<BPlusTreeNode<K, V>>>>,
+    ) -> bool {
+        let node = node_arc.read().unwrap();
 
-                // Add separator key (first key of child, except for first child)
-                if !current_internal.keys.is_empty() || i > 0 {
-                    let first_key = child.read().unwrap().keys[0].clone();
-                    current_internal.keys.push(first_key);
-                }
-            }
+        if node.keys.len() > node.max_keys || node.keys.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return false;
+        }
+        if node.keys.first().zip(lower).is_some_and(|(first, lower)| first < lower) || node.keys.last().zip(upper).is_some_and(|(last, upper)| last >= upper) {
+            return false;
+        }
 
-            // Add the last internal node
-            if !current_internal.children.is_empty() {
-                next_level.push(Arc::new(RwLock::new(current_internal)));
+        if node.is_leaf() {
+            if node.values.len() != node.keys.len() || !node.children.is_empty() || *leaf_depth.get_or_insert(depth) != depth {
+                return false;
             }
-
-            current_level = next_level;
+            leaves.push(node_arc.clone());
+            return true;
         }
 
-        // Set root
-        if let Some(root) = current_level.into_iter().next() {
-            self.root = Some(root);
+        if node.keys.is_empty() || node.children.len() != node.keys.len() + 1 {
+            return false;
         }
 
-        Ok(())
+        node.children.iter().enumerate().all(|(i, child)| {
+            let child_lower = if i == 0 { lower } else { Some(&node.keys[i - 1]) };
+            let child_upper = node.keys.get(i).or(upper);
+            Self::verify_node(child, child_lower, child_upper, depth + 1, leaf_depth, leaves)
+        })
     }
 
     /// Create a snapshot of the current tree state
@@ -606,6 +659,11 @@ where
     }
 }
 
+/// Sizes of `parts` consecutive chunks covering `total` items, differing by at most one
+fn even_chunks(total: usize, parts: usize) -> impl Iterator<Item = usize> {
+    (0..parts).map(move |i| total / parts + usize::from(i < total % parts))
+}
+
 impl<K, V> Default for BPlusTree<K, V>
 where
     K: IndexKey,
@@ -851,8 +909,32 @@ where
     }
 
     fn verify(&self) -> IndexResult<bool> {
-        // TODO: Implement tree structure verification
-        Ok(true)
+        let Some(root) = &self.root else {
+            return Ok(self.size == 0);
+        };
+
+        let mut leaves = Vec::new();
+        if !Self::verify_node(root, None, None, 0, &mut None, &mut leaves) {
+            return Ok(false);
+        }
+
+        // The leaf chain must visit exactly the leaves reachable from the root, in order
+        let mut count = 0;
+        let mut current = leaves.first().cloned();
+        for expected in &leaves {
+            let Some(node_arc) = current.take() else {
+                return Ok(false);
+            };
+            if !Arc::ptr_eq(&node_arc, expected) {
+                return Ok(false);
+            }
+
+            let node = node_arc.read().unwrap();
+            count += node.keys.len();
+            current = node.next_leaf.clone();
+        }
+
+        Ok(current.is_none() && count == self.size)
     }
 
     fn stats(&self) -> IndexStats {
@@ -1290,4 +1372,95 @@ mod tests {
         assert!(stats.type_specific.contains_key("depth"));
         assert!(stats.type_specific.contains_key("order"));
     }
+
+    fn leaf_count(tree: &BPlusTree<i32, String>) -> usize {
+        let mut count = 0;
+        let mut current = tree.first_leaf();
+        while let Some(node) = current {
+            count += 1;
+            current = node.read().unwrap().next_leaf.clone();
+        }
+        count
+    }
+
+    #[test]
+    fn test_bplus_tree_bulk_load() {
+        // Reverse order exercises the sort
+        let entries: Vec<_> = (0..10_000).rev().map(|i| (i, format!("value_{}", i))).collect();
+        let tree = BPlusTree::from_entries(8, 0.75, entries).unwrap();
+
+        assert_eq!(tree.len(), 10_000);
+        assert!(tree.verify().unwrap());
+        assert_eq!(tree.keys(), (0..10_000).collect::<Vec<_>>());
+        for i in [0, 1, 4_999, 9_999] {
+            assert_eq!(tree.get(&i).unwrap(), Some(format!("value_{}", i)));
+        }
+        assert_eq!(tree.get(&10_000).unwrap(), None);
+        assert_eq!(tree.range(&100, &104).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_bplus_tree_bulk_load_small_inputs() {
+        for len in 0..50 {
+            let entries: Vec<_> = (0..len).map(|i| (i, i.to_string())).collect();
+            let tree = BPlusTree::from_entries(MIN_ORDER, 1.0, entries).unwrap();
+            assert_eq!(tree.len(), len as usize);
+            assert!(tree.verify().unwrap(), "invalid tree for {} entries", len);
+            assert_eq!(tree.keys(), (0..len).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_bplus_tree_bulk_load_rejects_duplicates() {
+        let mut tree = BPlusTree::new();
+        tree.insert(42, "existing".to_string()).unwrap();
+
+        let result = tree.bulk_load(vec![(1, "a".to_string()), (2, "b".to_string()), (1, "c".to_string())]);
+        assert!(matches!(result, Err(IndexError::InvalidOperation(_))));
+
+        // A rejected load leaves the tree untouched, as a rejected insert does
+        assert_eq!(tree.keys(), vec![42]);
+        assert!(tree.insert(42, "again".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_bplus_tree_fill_factor() {
+        let entries: Vec<_> = (0..1_000).map(|i| (i, i.to_string())).collect();
+        let dense = BPlusTree::from_entries(12, 1.0, entries.clone()).unwrap();
+        let sparse = BPlusTree::from_entries(12, 0.5, entries).unwrap();
+
+        assert_eq!(leaf_count(&dense), 100);
+        assert_eq!(leaf_count(&sparse), 200);
+        assert!(sparse.verify().unwrap());
+
+        assert!(BPlusTree::<i32, String>::from_entries(11, 0.0, Vec::new()).is_err());
+        assert!(BPlusTree::<i32, String>::from_entries(11, 1.5, Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_bplus_tree_insert_after_bulk_load() {
+        let entries: Vec<_> = (0..2_000).map(|i| (i * 2, i.to_string())).collect();
+        let mut tree = BPlusTree::from_entries(6, 1.0, entries).unwrap();
+
+        for i in 0..2_000 {
+            tree.insert(i * 2 + 1, "odd".to_string()).unwrap();
+        }
+
+        assert_eq!(tree.len(), 4_000);
+        assert!(tree.verify().unwrap());
+        assert_eq!(tree.keys(), (0..4_000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_bplus_tree_verify_incremental() {
+        let mut tree = BPlusTree::with_order(4);
+        for i in (0..3_000).map(|i| (i * 7919) % 3_000) {
+            tree.insert(i, i.to_string()).unwrap();
+        }
+
+        assert!(tree.verify().unwrap());
+        for i in 0..3_000 {
+            assert_eq!(tree.get(&i).unwrap(), Some(i.to_string()));
+        }
+    }
 }
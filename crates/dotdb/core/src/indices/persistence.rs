@@ -16,6 +16,8 @@
 
 use super::lib::{IndexError, IndexKey, IndexResult, IndexType, IndexValue};
 use crate::memory::mmap::{MappingStrategy, MemoryMap};
+use crate::storage_engine::file_format::{PageId, PageType};
+use crate::storage_engine::lib::StorageError;
 use std::collections::HashMap;
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
@@ -90,6 +92,8 @@ pub struct IndexMetadata {
     pub is_mmap: bool,
     /// Checksum for integrity verification
     pub checksum: u64,
+    /// Index pages holding the index, in order, when it is stored through the page manager
+    pub pages: Vec<PageId>,
 }
 
 impl IndexPersistenceManager {
@@ -134,6 +138,7 @@ impl IndexPersistenceManager {
             format_version: self.format_version,
             is_mmap: false,
             checksum: 0,
+            pages: Vec::new(),
         };
 
         self.metadata.insert(name, metadata);
@@ -164,7 +169,14 @@ impl IndexPersistenceManager {
             metadata.file_path.clone()
         };
 
-        if use_mmap {
+        if self.page_manager.is_some() {
+            // Store the index in the storage file, on pages tagged as index pages
+            let pages = self.write_index_pages(name, &data)?;
+
+            let metadata = self.metadata.get_mut(name).unwrap();
+            metadata.pages = pages;
+            metadata.is_mmap = false;
+        } else if use_mmap {
             // Create memory-mapped file
             let mmap = self.create_mmap_file(&file_path, data.len())?;
 
@@ -213,7 +225,9 @@ impl IndexPersistenceManager {
         let is_mmap = metadata.is_mmap;
         let file_path = metadata.file_path.clone();
 
-        let data = if is_mmap {
+        let data = if !metadata.pages.is_empty() {
+            self.read_index_pages(metadata)?
+        } else if is_mmap {
             // Read from memory-mapped file
             let mmap = self.mmapped_indices.get(name).ok_or_else(|| IndexError::InvalidOperation("Memory map not found".to_string()))?;
 
@@ -240,6 +254,49 @@ impl IndexPersistenceManager {
         Ok(())
     }
 
+    /// Write index data to freshly allocated index pages, freeing the pages of the previous save
+    fn write_index_pages(&self, name: &str, data: &[u8]) -> IndexResult<Vec<PageId>> {
+        let page_manager = self.page_manager.as_ref().ok_or_else(|| IndexError::InvalidOperation("No page manager set".to_string()))?;
+        let mut page_manager = page_manager.write().map_err(|_| IndexError::Corruption("Failed to acquire write lock".to_string()))?;
+
+        let previous = self.metadata.get(name).map(|metadata| metadata.pages.clone()).unwrap_or_default();
+        for page_id in previous {
+            page_manager.free_page(page_id, PageType::Index).map_err(|e| Self::page_error(name, e))?;
+        }
+
+        let capacity = page_manager.page_capacity().map_err(|e| Self::page_error(name, e))?;
+        let mut pages = Vec::with_capacity(data.len().div_ceil(capacity));
+        for chunk in data.chunks(capacity) {
+            let allocation = page_manager.allocate_page(PageType::Index).map_err(|e| Self::page_error(name, e))?;
+            page_manager.write_page_data(&allocation, chunk).map_err(|e| Self::page_error(name, e))?;
+            pages.push(allocation.page_id);
+        }
+
+        Ok(pages)
+    }
+
+    /// Read an index back from its index pages
+    fn read_index_pages(&self, metadata: &IndexMetadata) -> IndexResult<Vec<u8>> {
+        let page_manager = self.page_manager.as_ref().ok_or_else(|| IndexError::InvalidOperation("No page manager set".to_string()))?;
+        let page_manager = page_manager.read().map_err(|_| IndexError::Corruption("Failed to acquire read lock".to_string()))?;
+
+        let mut data = Vec::with_capacity(metadata.disk_size as usize);
+        for &page_id in &metadata.pages {
+            data.extend(page_manager.read_page_data(page_id).map_err(|e| Self::page_error(&metadata.name, e))?);
+        }
+        data.truncate(metadata.disk_size as usize);
+
+        Ok(data)
+    }
+
+    /// Map a storage error on an index page to an index error, keeping corruption distinguishable
+    fn page_error(name: &str, error: StorageError) -> IndexError {
+        match error {
+            StorageError::Corruption(msg) => IndexError::Corruption(format!("index {name}: {msg}")),
+            other => IndexError::IoError(format!("index {name}: {other}")),
+        }
+    }
+
     /// Create a memory-mapped file for an index
     fn create_mmap_file(&self, path: &Path, size: usize) -> IndexResult<Arc<RwLock<MemoryMap>>> {
         // Ensure file exists and has the right size
@@ -346,6 +403,14 @@ impl IndexPersistenceManager {
             // Remove memory mapping if it exists
             self.mmapped_indices.remove(name);
 
+            // Return its index pages to the page manager
+            if let Some(page_manager) = &self.page_manager {
+                let mut page_manager = page_manager.write().map_err(|_| IndexError::Corruption("Failed to acquire write lock".to_string()))?;
+                for &page_id in &metadata.pages {
+                    page_manager.free_page(page_id, PageType::Index).map_err(|e| Self::page_error(name, e))?;
+                }
+            }
+
             // Remove file from disk
             if metadata.file_path.exists() {
                 std::fs::remove_file(&metadata.file_path).map_err(|e| IndexError::IoError(format!("Failed to remove file: {e}")))?;
@@ -388,6 +453,14 @@ impl IndexPersistenceManager {
 
     /// Verify integrity of a specific index
     fn verify_index_integrity(&self, metadata: &IndexMetadata) -> IndexResult<bool> {
+        if !metadata.pages.is_empty() {
+            return match self.read_index_pages(metadata) {
+                Ok(data) => Ok(self.calculate_checksum(&data) == metadata.checksum),
+                Err(IndexError::Corruption(_)) => Ok(false),
+                Err(e) => Err(e),
+            };
+        }
+
         if !metadata.file_path.exists() {
             return Ok(false);
         }
@@ -575,6 +648,60 @@ mod tests {
         assert_eq!(metadata.index_type, IndexType::BPlusTree);
     }
 
+    #[test]
+    fn test_index_saved_through_page_manager_uses_index_pages() {
+        use crate::indices::{HashIndex, Index};
+        use crate::storage_engine::eviction::ReplacementPolicy;
+        use crate::storage_engine::file_format::FileFormat;
+        use crate::storage_engine::lib::StorageConfig;
+        use crate::storage_engine::page_manager::PageManager;
+        use std::sync::Mutex;
+
+        let temp_dir = TempDir::new().unwrap();
+        let config = StorageConfig {
+            path: temp_dir.path().join("indices.dotdb"),
+            page_size: 4096,
+            buffer_pool_size: 100,
+            eviction_policy: ReplacementPolicy::LRU,
+            direct_io: false,
+            io_backend: Default::default(),
+            wal_size: 1024 * 1024,
+            flush_interval_ms: 100,
+            max_dirty_pages: 10,
+            writer_threads: 1,
+            compaction_threads: None,
+            compaction_io_budget_bytes_per_sec: 0,
+            deadlock_policy: Default::default(),
+        };
+        let mut file_format = FileFormat::new(config);
+        file_format.init().unwrap();
+        let file_format = Arc::new(Mutex::new(file_format));
+
+        let mut manager = IndexPersistenceManager::new(temp_dir.path()).unwrap();
+        manager.set_page_manager(Arc::new(RwLock::new(PageManager::new(file_format.clone()))));
+        manager.register_index("by_name".to_string(), IndexType::Hash).unwrap();
+
+        let mut index = HashIndex::new();
+        for i in 0..500u64 {
+            index.insert(i, format!("value {i}")).unwrap();
+        }
+        manager.save_index("by_name", &index, false).unwrap();
+
+        // The index lives in the storage file, spread over pages tagged as index pages
+        let metadata = manager.get_metadata("by_name").unwrap().clone();
+        assert!(metadata.pages.len() > 1);
+        assert!(!metadata.file_path.exists());
+        for &page_id in &metadata.pages {
+            assert_eq!(file_format.lock().unwrap().read_page(page_id).unwrap().header.page_type, PageType::Index);
+        }
+        assert!(manager.verify_all().unwrap()["by_name"]);
+
+        let mut loaded: HashIndex<u64, String> = HashIndex::new();
+        manager.load_index("by_name", &mut loaded).unwrap();
+        assert_eq!(loaded.get(&42).unwrap(), Some("value 42".to_string()));
+        assert_eq!(loaded.get(&499).unwrap(), Some("value 499".to_string()));
+    }
+
     #[test]
     fn test_serialization_format() {
         let format = IndexSerializationFormat::new(IndexType::BPlusTree, 100, 1000);
@@ -0,0 +1,153 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::alloc::{self, Layout};
+use std::fs::File;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+
+use crate::memory::lib::{align_to, get_page_size, is_power_of_two};
+
+/// Logical block size assumed when the device does not report one
+pub const DEFAULT_LOGICAL_BLOCK_SIZE: usize = 512;
+
+/// A zeroed heap buffer whose address is a multiple of its alignment, as `O_DIRECT`
+/// requires of the buffers it transfers. The allocation is rounded up to the alignment,
+/// so a buffer of whole logical blocks can be read into or written from directly.
+pub struct AlignedBuffer {
+    ptr: NonNull<u8>,
+    len: usize,
+    layout: Layout,
+}
+
+// The buffer owns its allocation exclusively, like a `Vec<u8>`
+unsafe impl Send for AlignedBuffer {}
+unsafe impl Sync for AlignedBuffer {}
+
+impl AlignedBuffer {
+    /// Allocate `len` bytes aligned to `alignment`, which must be a power of two
+    pub fn new(len: usize, alignment: usize) -> Self {
+        assert!(is_power_of_two(alignment), "alignment must be a power of two, got {alignment}");
+        let layout = Layout::from_size_align(align_to(len.max(1), alignment), alignment).expect("aligned buffer layout");
+        // SAFETY: the layout has a non-zero size
+        let ptr = unsafe { alloc::alloc_zeroed(layout) };
+        let ptr = NonNull::new(ptr).unwrap_or_else(|| alloc::handle_alloc_error(layout));
+        Self { ptr, len, layout }
+    }
+
+    /// Allocate `len` bytes aligned to the system page size, which satisfies the
+    /// alignment of every logical block size in use
+    pub fn page_aligned(len: usize) -> Self {
+        Self::new(len, get_page_size())
+    }
+
+    pub fn alignment(&self) -> usize {
+        self.layout.align()
+    }
+}
+
+impl Deref for AlignedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: `ptr` points to at least `len` initialized bytes owned by this buffer
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for AlignedBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: as for `deref`, and `&mut self` guarantees exclusive access
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        // SAFETY: allocated in `new` with this layout
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) }
+    }
+}
+
+impl std::fmt::Debug for AlignedBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AlignedBuffer").field("len", &self.len).field("alignment", &self.alignment()).finish()
+    }
+}
+
+/// Whether `buffer` can be handed to direct IO as is: its address and length must be
+/// multiples of `block_size`
+pub fn is_aligned(buffer: &[u8], block_size: usize) -> bool {
+    (buffer.as_ptr() as usize).is_multiple_of(block_size) && buffer.len().is_multiple_of(block_size)
+}
+
+/// The logical block size of the device behind `file`, which direct IO offsets and
+/// lengths must be multiples of
+///
+/// Block devices report it directly. For regular files 4096 bytes is used, or the page
+/// size if that is smaller: filesystems use logical blocks of at most 4096 bytes, so IO
+/// aligned to it is accepted everywhere.
+pub fn logical_block_size(file: &File) -> usize {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        use std::os::unix::io::AsRawFd;
+
+        if file.metadata().is_ok_and(|metadata| metadata.file_type().is_block_device()) {
+            let mut size: libc::c_int = 0;
+            // SAFETY: BLKSSZGET writes a single int through the pointer
+            if unsafe { libc::ioctl(file.as_raw_fd(), libc::BLKSSZGET, &mut size) } == 0 && size > 0 && is_power_of_two(size as usize) {
+                return size as usize;
+            }
+            return DEFAULT_LOGICAL_BLOCK_SIZE;
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = file;
+
+    get_page_size().min(4096)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aligned_buffer() {
+        let mut buffer = AlignedBuffer::new(8192, 4096);
+        assert_eq!(buffer.len(), 8192);
+        assert!(is_aligned(&buffer, 4096));
+        assert!(buffer.iter().all(|&b| b == 0));
+
+        buffer[..5].copy_from_slice(b"hello");
+        assert_eq!(&buffer[..5], b"hello");
+
+        // Partial blocks are addressable but not transferable as is
+        let partial = AlignedBuffer::new(100, 512);
+        assert_eq!((partial.len(), partial.alignment()), (100, 512));
+        assert!(!is_aligned(&partial, 512));
+        assert!(is_aligned(&partial[..0], 512));
+        assert_eq!(AlignedBuffer::page_aligned(10).alignment(), get_page_size());
+    }
+
+    #[test]
+    fn test_logical_block_size_of_regular_file() {
+        let file = tempfile::tempfile().unwrap();
+        let block_size = logical_block_size(&file);
+        assert!(is_power_of_two(block_size));
+        assert_eq!(4096 % block_size, 0);
+    }
+}
@@ -18,6 +18,8 @@ use std::fs::{File, OpenOptions};
 use std::io::{Read, Result, Seek, SeekFrom, Write};
 use std::path::Path;
 
+use crate::io::aligned::AlignedBuffer;
+
 /// Configuration for Direct I/O operations, including block size, alignment, and buffer size.
 #[derive(Debug, Clone)]
 pub struct DirectIOConfig {
@@ -95,7 +97,7 @@ impl DirectIOFile {
         }
 
         let aligned_size = self.align_size(data.len());
-        let mut aligned_buffer = vec![0u8; aligned_size];
+        let mut aligned_buffer = AlignedBuffer::new(aligned_size, self.config.alignment);
         aligned_buffer[..data.len()].copy_from_slice(data);
 
         let bytes_written = self.file.write(&aligned_buffer)?;
@@ -110,7 +112,7 @@ impl DirectIOFile {
         }
 
         let aligned_size = self.align_size(buf.len());
-        let mut aligned_buffer = vec![0u8; aligned_size];
+        let mut aligned_buffer = AlignedBuffer::new(aligned_size, self.config.alignment);
 
         let bytes_read = self.file.read(&mut aligned_buffer)?;
         let copy_size = buf.len().min(bytes_read);
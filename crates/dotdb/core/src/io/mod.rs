@@ -14,6 +14,14 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+pub mod aligned;
 pub mod direct_io;
+pub mod page_io;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub mod uring;
 
+pub use aligned::*;
 pub use direct_io::*;
+pub use page_io::*;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub use uring::*;
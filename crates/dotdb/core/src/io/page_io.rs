@@ -0,0 +1,239 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Page IO backends implementing [`AsyncIO`]
+//!
+//! Pages are read and written at `page_id * page_size`. With direct IO every transfer
+//! bypasses the page cache, so offsets, lengths and buffer addresses must be multiples
+//! of the device's logical block size: the page size is checked against it when the
+//! file is opened, and buffers that are not aligned go through an aligned bounce buffer.
+
+use std::fs::File;
+use std::io;
+#[cfg(unix)]
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+use tracing::warn;
+
+use crate::io::aligned::{AlignedBuffer, is_aligned, logical_block_size};
+use crate::storage_engine::lib::{AsyncIO, IoBackend, StorageConfig, StorageDevice, StorageError, StorageResult, open_storage};
+
+/// Open `path` for page IO, with `O_DIRECT` if `direct_io` is set. Filesystems that do
+/// not support direct IO, such as tmpfs, refuse it with `EINVAL`; the file is then opened
+/// buffered and the returned flag says so.
+pub fn open_page_file(path: &Path, direct_io: bool) -> StorageResult<(File, bool)> {
+    if !cfg!(target_os = "linux") || !direct_io {
+        return Ok((open_storage(path, StorageDevice::File, true, false)?, false));
+    }
+
+    match open_storage(path, StorageDevice::File, true, true) {
+        Ok(file) => Ok((file, true)),
+        Err(StorageError::Io(e)) if e.raw_os_error() == Some(libc::EINVAL) => {
+            warn!("{} does not support direct IO, falling back to buffered IO", path.display());
+            Ok((open_storage(path, StorageDevice::File, true, false)?, false))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Check that pages of `page_size` bytes can be transferred with direct IO on a device
+/// with the given logical block size
+pub fn check_direct_page_size(page_size: usize, block_size: usize) -> StorageResult<()> {
+    if page_size == 0 || !page_size.is_multiple_of(block_size) {
+        return Err(StorageError::InvalidConfig {
+            setting: "page_size".to_string(),
+            reason: format!("{} is not a multiple of the device's logical block size {}, which direct IO requires", page_size, block_size),
+        });
+    }
+    Ok(())
+}
+
+/// Open the page IO backend `config` asks for on the file at `path`
+pub fn open_page_io(path: &Path, config: &StorageConfig) -> StorageResult<Box<dyn AsyncIO>> {
+    match config.io_backend {
+        IoBackend::Pread => Ok(Box::new(PreadPageIO::open(path, config.page_size, config.direct_io)?)),
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        IoBackend::IoUring { queue_depth } => match crate::io::uring::UringPageIO::open(path, config.page_size, config.direct_io, queue_depth) {
+            Ok(io) => Ok(Box::new(io)),
+            Err(StorageError::Io(e)) => {
+                warn!("Could not set up io_uring ({}), falling back to pread/pwrite", e);
+                Ok(Box::new(PreadPageIO::open(path, config.page_size, config.direct_io)?))
+            }
+            Err(e) => Err(e),
+        },
+        #[cfg(not(all(target_os = "linux", feature = "io-uring")))]
+        IoBackend::IoUring { .. } => {
+            warn!("io_uring needs Linux and the io-uring feature, falling back to pread/pwrite");
+            Ok(Box::new(PreadPageIO::open(path, config.page_size, config.direct_io)?))
+        }
+    }
+}
+
+/// Pages of one file, read and written with positional `pread`/`pwrite` calls
+pub struct PreadPageIO {
+    file: File,
+    page_size: usize,
+    block_size: usize,
+    direct: bool,
+}
+
+impl PreadPageIO {
+    /// Open the file at `path`, falling back to buffered IO if direct IO is unsupported
+    pub fn open(path: &Path, page_size: usize, direct_io: bool) -> StorageResult<Self> {
+        let (file, direct) = open_page_file(path, direct_io)?;
+        let block_size = logical_block_size(&file);
+        if direct {
+            check_direct_page_size(page_size, block_size)?;
+        }
+        Ok(Self { file, page_size, block_size, direct })
+    }
+
+    /// Whether transfers bypass the page cache
+    pub fn is_direct(&self) -> bool {
+        self.direct
+    }
+
+    fn offset(&self, page_id: u64) -> u64 {
+        page_id * self.page_size as u64
+    }
+}
+
+impl AsyncIO for PreadPageIO {
+    fn read_page(&self, page_id: u64, buffer: &mut [u8]) -> StorageResult<usize> {
+        let len = check_page_buffer(buffer.len(), self.page_size)?;
+        let offset = self.offset(page_id);
+        if !self.direct || is_aligned(buffer, self.block_size) {
+            return read_full_at(&self.file, &mut buffer[..len], offset);
+        }
+
+        let mut bounce = AlignedBuffer::page_aligned(self.page_size);
+        let read = read_full_at(&self.file, &mut bounce[..self.page_size], offset)?;
+        buffer[..len].copy_from_slice(&bounce[..len]);
+        Ok(read)
+    }
+
+    fn write_page(&self, page_id: u64, buffer: &[u8]) -> StorageResult<usize> {
+        let len = check_page_buffer(buffer.len(), self.page_size)?;
+        let offset = self.offset(page_id);
+        if !self.direct || is_aligned(buffer, self.block_size) {
+            self.file.write_all_at(&buffer[..len], offset)?;
+            return Ok(len);
+        }
+
+        let mut bounce = AlignedBuffer::page_aligned(self.page_size);
+        bounce[..len].copy_from_slice(&buffer[..len]);
+        self.file.write_all_at(&bounce[..self.page_size], offset)?;
+        Ok(len)
+    }
+
+    fn sync(&self) -> StorageResult<()> {
+        self.file.sync_data()?;
+        Ok(())
+    }
+}
+
+/// Buffers hold exactly one page
+pub(crate) fn check_page_buffer(len: usize, page_size: usize) -> StorageResult<usize> {
+    if len != page_size {
+        return Err(StorageError::InvalidOperation(format!("Page buffer of {} bytes for {} byte pages", len, page_size)));
+    }
+    Ok(len)
+}
+
+/// Read until `buffer` is full or the file ends, zero-filling past the end
+fn read_full_at(file: &File, buffer: &mut [u8], offset: u64) -> StorageResult<usize> {
+    let mut read = 0;
+    while read < buffer.len() {
+        match file.read_at(&mut buffer[read..], offset + read as u64) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    buffer[read..].fill(0);
+    Ok(read)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::lib::get_page_size;
+    use tempfile::tempdir;
+
+    fn page(fill: u8, page_size: usize) -> Vec<u8> {
+        (0..page_size).map(|i| fill.wrapping_add(i as u8)).collect()
+    }
+
+    fn round_trip(io: &dyn AsyncIO, page_size: usize) {
+        // An unaligned page goes through a bounce buffer under direct IO
+        let first = page(1, page_size);
+        assert_eq!(io.write_page(3, &first).unwrap(), page_size);
+
+        let mut aligned = AlignedBuffer::page_aligned(page_size);
+        aligned[..page_size].copy_from_slice(&page(7, page_size));
+        io.write_pages(&[(0, &aligned[..page_size]), (1, &page(9, page_size))]).unwrap();
+        io.sync().unwrap();
+
+        let mut buffer = vec![0; page_size];
+        assert_eq!(io.read_page(3, &mut buffer).unwrap(), page_size);
+        assert_eq!(buffer, first);
+
+        let (mut a, mut b) = (AlignedBuffer::page_aligned(page_size), vec![0; page_size]);
+        io.read_pages(&mut [(0, &mut a[..page_size]), (1, &mut b)]).unwrap();
+        assert_eq!(&a[..page_size], &page(7, page_size)[..]);
+        assert_eq!(b, page(9, page_size));
+
+        // Past the end of the file reads come back empty
+        let mut past_end = vec![1; page_size];
+        assert_eq!(io.read_page(10, &mut past_end).unwrap(), 0);
+        assert!(past_end.iter().all(|&b| b == 0));
+
+        assert!(io.read_page(0, &mut vec![0; page_size / 2]).is_err());
+    }
+
+    #[test]
+    fn test_pread_page_io() {
+        let dir = tempdir().unwrap();
+        let page_size = get_page_size();
+        for direct_io in [false, true] {
+            let path = dir.path().join(format!("pages-{direct_io}"));
+            let io = PreadPageIO::open(&path, page_size, direct_io).unwrap();
+            assert!(direct_io || !io.is_direct());
+            round_trip(&io, page_size);
+        }
+    }
+
+    #[test]
+    fn test_backend_selection_falls_back() {
+        let dir = tempdir().unwrap();
+        let config = StorageConfig {
+            page_size: get_page_size(),
+            io_backend: IoBackend::IoUring { queue_depth: 8 },
+            ..Default::default()
+        };
+        // Without io_uring support this is the pread backend; either way pages round-trip
+        let io = open_page_io(&dir.path().join("pages"), &config).unwrap();
+        round_trip(io.as_ref(), config.page_size);
+    }
+
+    #[test]
+    fn test_direct_page_size_must_match_block_size() {
+        assert!(check_direct_page_size(4096, 512).is_ok());
+        assert!(matches!(check_direct_page_size(1000, 512), Err(StorageError::InvalidConfig { .. })));
+    }
+}
@@ -0,0 +1,258 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Page IO through io_uring
+//!
+//! Batches of pages are submitted together, up to the queue depth at a time, and the
+//! caller waits once for the whole batch rather than once per page.
+
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use io_uring::{IoUring, opcode, types};
+use parking_lot::Mutex;
+
+use crate::io::aligned::{AlignedBuffer, is_aligned, logical_block_size};
+use crate::io::page_io::{check_direct_page_size, check_page_buffer, open_page_file};
+use crate::storage_engine::lib::{AsyncIO, StorageError, StorageResult};
+
+/// Pages of one file, read and written through an io_uring submission queue
+pub struct UringPageIO {
+    file: File,
+    ring: Mutex<IoUring>,
+    queue_depth: usize,
+    page_size: usize,
+    block_size: usize,
+    direct: bool,
+}
+
+/// One page transfer of a batch, with the bounce buffer it uses if the caller's buffer
+/// is not aligned for direct IO
+struct Transfer {
+    offset: u64,
+    bounce: Option<AlignedBuffer>,
+}
+
+impl UringPageIO {
+    /// Open the file at `path` with a ring of `queue_depth` entries. Fails with an IO
+    /// error if the kernel does not provide io_uring or refuses to set up the ring.
+    pub fn open(path: &Path, page_size: usize, direct_io: bool, queue_depth: u32) -> StorageResult<Self> {
+        if queue_depth == 0 {
+            return Err(StorageError::InvalidConfig {
+                setting: "io_backend".to_string(),
+                reason: "io_uring queue depth must be at least 1".to_string(),
+            });
+        }
+        let (file, direct) = open_page_file(path, direct_io)?;
+        let block_size = logical_block_size(&file);
+        if direct {
+            check_direct_page_size(page_size, block_size)?;
+        }
+        let ring = IoUring::new(queue_depth)?;
+        Ok(Self {
+            file,
+            ring: Mutex::new(ring),
+            queue_depth: queue_depth as usize,
+            page_size,
+            block_size,
+            direct,
+        })
+    }
+
+    /// Whether transfers bypass the page cache
+    pub fn is_direct(&self) -> bool {
+        self.direct
+    }
+
+    fn transfer(&self, page_id: u64, buffer: &[u8]) -> StorageResult<Transfer> {
+        check_page_buffer(buffer.len(), self.page_size)?;
+        let bounce = (self.direct && !is_aligned(buffer, self.block_size)).then(|| AlignedBuffer::page_aligned(self.page_size));
+        Ok(Transfer {
+            offset: page_id * self.page_size as u64,
+            bounce,
+        })
+    }
+
+    /// Submit one entry per transfer and wait for all of them, returning the bytes each moved
+    fn submit(&self, entries: Vec<io_uring::squeue::Entry>) -> StorageResult<Vec<usize>> {
+        let mut ring = self.ring.lock();
+        for entry in &entries {
+            // SAFETY: the buffers the entries point to outlive this call, which waits for
+            // every completion before returning
+            unsafe { ring.submission().push(entry) }.map_err(|_| StorageError::Buffer("io_uring submission queue is full".to_string()))?;
+        }
+
+        let mut results = vec![0; entries.len()];
+        let mut completed = 0;
+        // Keep draining after a failure so no completion refers to a buffer after we return
+        let mut failure = None;
+        while completed < entries.len() {
+            match ring.submit_and_wait(entries.len() - completed) {
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+            for cqe in ring.completion() {
+                completed += 1;
+                if cqe.result() < 0 {
+                    failure.get_or_insert(io::Error::from_raw_os_error(-cqe.result()));
+                } else {
+                    results[cqe.user_data() as usize] = cqe.result() as usize;
+                }
+            }
+        }
+        match failure {
+            Some(e) => Err(e.into()),
+            None => Ok(results),
+        }
+    }
+}
+
+impl AsyncIO for UringPageIO {
+    fn read_page(&self, page_id: u64, buffer: &mut [u8]) -> StorageResult<usize> {
+        self.read_pages(&mut [(page_id, &mut *buffer)])?;
+        // Bytes past the end of the file come back zeroed, so report what was there
+        let offset = page_id * self.page_size as u64;
+        let file_len = self.file.metadata()?.len();
+        Ok(file_len.saturating_sub(offset).min(self.page_size as u64) as usize)
+    }
+
+    fn write_page(&self, page_id: u64, buffer: &[u8]) -> StorageResult<usize> {
+        self.write_pages(&[(page_id, buffer)])?;
+        Ok(buffer.len())
+    }
+
+    fn sync(&self) -> StorageResult<()> {
+        self.file.sync_data()?;
+        Ok(())
+    }
+
+    fn read_pages(&self, requests: &mut [(u64, &mut [u8])]) -> StorageResult<()> {
+        let fd = types::Fd(self.file.as_raw_fd());
+        for batch in requests.chunks_mut(self.queue_depth) {
+            let mut transfers = Vec::with_capacity(batch.len());
+            for (page_id, buffer) in batch.iter() {
+                transfers.push(self.transfer(*page_id, buffer)?);
+            }
+
+            let entries = batch
+                .iter_mut()
+                .zip(transfers.iter_mut())
+                .enumerate()
+                .map(|(i, ((_, buffer), transfer))| {
+                    let target = match transfer.bounce.as_mut() {
+                        Some(bounce) => &mut bounce[..self.page_size],
+                        None => &mut **buffer,
+                    };
+                    opcode::Read::new(fd, target.as_mut_ptr(), target.len() as u32).offset(transfer.offset).build().user_data(i as u64)
+                })
+                .collect();
+            let results = self.submit(entries)?;
+
+            for (((_, buffer), transfer), read) in batch.iter_mut().zip(transfers).zip(results) {
+                if let Some(bounce) = transfer.bounce {
+                    buffer.copy_from_slice(&bounce[..self.page_size]);
+                }
+                // A short read only happens at the end of the file
+                buffer[read..].fill(0);
+            }
+        }
+        Ok(())
+    }
+
+    fn write_pages(&self, requests: &[(u64, &[u8])]) -> StorageResult<()> {
+        let fd = types::Fd(self.file.as_raw_fd());
+        for batch in requests.chunks(self.queue_depth) {
+            let mut transfers = Vec::with_capacity(batch.len());
+            for (page_id, buffer) in batch {
+                let mut transfer = self.transfer(*page_id, buffer)?;
+                if let Some(bounce) = transfer.bounce.as_mut() {
+                    bounce[..self.page_size].copy_from_slice(buffer);
+                }
+                transfers.push(transfer);
+            }
+
+            let entries = batch
+                .iter()
+                .zip(transfers.iter())
+                .enumerate()
+                .map(|(i, ((_, buffer), transfer))| {
+                    let source = match transfer.bounce.as_ref() {
+                        Some(bounce) => &bounce[..self.page_size],
+                        None => *buffer,
+                    };
+                    opcode::Write::new(fd, source.as_ptr(), source.len() as u32).offset(transfer.offset).build().user_data(i as u64)
+                })
+                .collect();
+
+            for written in self.submit(entries)? {
+                if written != self.page_size {
+                    return Err(StorageError::Io(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        format!("io_uring wrote {} of {} bytes", written, self.page_size),
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::lib::get_page_size;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_uring_page_io() {
+        let dir = tempdir().unwrap();
+        let page_size = get_page_size();
+        for direct_io in [false, true] {
+            let io = match UringPageIO::open(&dir.path().join(format!("pages-{direct_io}")), page_size, direct_io, 4) {
+                Ok(io) => io,
+                // Sandboxes commonly forbid io_uring_setup
+                Err(StorageError::Io(e)) => {
+                    eprintln!("skipping io_uring test: {e}");
+                    return;
+                }
+                Err(e) => panic!("{e}"),
+            };
+
+            // More pages than the queue depth, half of them unaligned
+            let pages: Vec<Vec<u8>> = (0..10u8).map(|i| vec![i + 1; page_size]).collect();
+            let requests: Vec<(u64, &[u8])> = pages.iter().enumerate().map(|(i, page)| (i as u64, page.as_slice())).collect();
+            io.write_pages(&requests).unwrap();
+            let mut aligned = AlignedBuffer::page_aligned(page_size);
+            aligned.fill(42);
+            io.write_page(10, &aligned[..page_size]).unwrap();
+            io.sync().unwrap();
+
+            let mut buffers = vec![vec![0u8; page_size]; 10];
+            let mut requests: Vec<(u64, &mut [u8])> = buffers.iter_mut().enumerate().map(|(i, b)| (i as u64, b.as_mut_slice())).collect();
+            io.read_pages(&mut requests).unwrap();
+            assert_eq!(buffers, pages);
+
+            let mut buffer = vec![0; page_size];
+            assert_eq!(io.read_page(10, &mut buffer).unwrap(), page_size);
+            assert!(buffer.iter().all(|&b| b == 42));
+            assert_eq!(io.read_page(11, &mut buffer).unwrap(), 0);
+            assert!(buffer.iter().all(|&b| b == 0));
+        }
+    }
+}
@@ -15,6 +15,9 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::statistics::calibration::{CostProfile, load_cost_profile};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CostEstimate {
@@ -62,9 +65,12 @@ pub enum OperationCost {
 pub struct CostModel {
     cpu_cost_per_row: f64,
     io_cost_per_page: f64,
+    random_io_cost_per_page: f64,
+    hash_probe_cost: f64,
     memory_cost_per_mb: f64,
     sort_factor: f64,
     join_factor: f64,
+    profile_version: Option<u64>,
 }
 
 impl CostModel {
@@ -72,12 +78,40 @@ impl CostModel {
         Self {
             cpu_cost_per_row: 0.01,
             io_cost_per_page: 1.0,
+            random_io_cost_per_page: 1.0,
+            hash_probe_cost: 0.01,
             memory_cost_per_mb: 0.1,
             sort_factor: 1.5,
             join_factor: 2.0,
+            profile_version: None,
         }
     }
 
+    /// Cost model using the constants of a calibrated profile
+    pub fn from_profile(profile: &CostProfile) -> Self {
+        Self {
+            cpu_cost_per_row: profile.cpu_cost_per_row,
+            io_cost_per_page: profile.sequential_page_cost,
+            random_io_cost_per_page: profile.random_page_cost,
+            hash_probe_cost: profile.hash_probe_cost,
+            profile_version: Some(profile.version),
+            ..Self::new()
+        }
+    }
+
+    /// Cost model for a data directory: its calibrated profile if there is one, the defaults otherwise
+    pub fn load(data_dir: &Path) -> Self {
+        match load_cost_profile(data_dir) {
+            Ok(Some(profile)) => Self::from_profile(&profile),
+            _ => Self::new(),
+        }
+    }
+
+    /// Version of the calibrated profile in use, `None` for the defaults
+    pub fn profile_version(&self) -> Option<u64> {
+        self.profile_version
+    }
+
     pub fn estimate_operation_cost(&self, operation: &OperationCost) -> CostEstimate {
         match operation {
             OperationCost::TableScan { rows, selectivity } => {
@@ -87,7 +121,7 @@ impl CostModel {
                 CostEstimate::new(cpu_cost, io_cost, 0.0, 0.0)
             }
             OperationCost::IndexScan { rows, index_pages } => {
-                let io_cost = *index_pages as f64 * self.io_cost_per_page;
+                let io_cost = *index_pages as f64 * self.random_io_cost_per_page; // Index lookups read pages out of order
                 let cpu_cost = *rows as f64 * self.cpu_cost_per_row * 0.5; // Index scan is more efficient
                 CostEstimate::new(cpu_cost, io_cost, 0.0, 0.0)
             }
@@ -98,7 +132,7 @@ impl CostModel {
                 CostEstimate::new(cpu_cost, 0.0, memory_cost, 0.0)
             }
             OperationCost::Join { left_rows, right_rows, join_type } => {
-                let base_cost = (*left_rows as f64 * (*right_rows).min(1000) as f64) * self.cpu_cost_per_row;
+                let base_cost = (*left_rows as f64 * (*right_rows).min(1000) as f64) * self.hash_probe_cost;
                 let type_multiplier = match join_type.as_str() {
                     "INNER" => 1.0,
                     "LEFT" | "RIGHT" => 1.2,
@@ -111,7 +145,7 @@ impl CostModel {
                 CostEstimate::new(cpu_cost, 0.0, memory_cost, 0.0)
             }
             OperationCost::Aggregate { rows, groups } => {
-                let cpu_cost = *rows as f64 * self.cpu_cost_per_row;
+                let cpu_cost = *rows as f64 * self.hash_probe_cost;
                 let memory_cost = (*groups as f64 * 64.0) / (1024.0 * 1024.0) * self.memory_cost_per_mb;
                 CostEstimate::new(cpu_cost, 0.0, memory_cost, 0.0)
             }
@@ -166,4 +200,24 @@ mod tests {
         assert!(cost.cpu_cost > 0.0);
         assert!(cost.memory_cost > 0.0);
     }
+
+    #[test]
+    fn test_profile_prices_random_io() {
+        let mut profile = CostProfile::from_measurements(crate::statistics::CalibrationMeasurements {
+            page_size: 8192,
+            sequential_page_read_ns: 1000.0,
+            random_page_read_ns: 4000.0,
+            comparison_ns: 10.0,
+            hash_probe_ns: 20.0,
+        });
+        profile.version = 7;
+
+        let model = CostModel::from_profile(&profile);
+        assert_eq!(model.profile_version(), Some(7));
+        let index_scan = model.estimate_operation_cost(&OperationCost::IndexScan { rows: 0, index_pages: 10 });
+        assert_eq!(index_scan.io_cost, 40.0);
+
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(CostModel::load(dir.path()).profile_version(), None);
+    }
 }
@@ -38,6 +38,9 @@ pub struct QueryPlan {
     pub estimated_cost: CostEstimate,
     pub estimated_rows: u64,
     pub parallelism_degree: usize,
+    /// Calibrated cost profile the plan was costed with, `None` for the default constants
+    #[serde(default)]
+    pub cost_profile_version: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -179,6 +182,12 @@ impl QueryPlanner {
         }
     }
 
+    /// Use `cost_model` instead of the default constants, e.g. one loaded from a calibrated profile
+    pub fn with_cost_model(mut self, cost_model: CostModel) -> Self {
+        self.cost_model = cost_model;
+        self
+    }
+
     pub fn register_table(&mut self, table_name: String, metadata: TableMetadata) {
         self.table_metadata.insert(table_name, metadata);
     }
@@ -221,21 +230,30 @@ impl QueryPlanner {
             plan_alternatives.push(current_plan);
         }
 
-        // Select the best plan based on cost
-        let best_plan = plan_alternatives
+        // Select the best plan based on the cost of the whole tree
+        let (best_plan, estimated_cost) = plan_alternatives
             .into_iter()
-            .min_by(|a, b| a.estimated_cost.total_cost.partial_cmp(&b.estimated_cost.total_cost).unwrap())
+            .map(|plan| {
+                let cost = Self::total_cost(&plan);
+                (plan, cost)
+            })
+            .min_by(|(_, a), (_, b)| a.total_cost.total_cmp(&b.total_cost))
             .ok_or_else(|| PlanGeneratorError::NoExecutionPlan("No valid plans generated".to_string()))?;
 
         Ok(QueryPlan {
             plan_id: format!("plan_{}", uuid::Uuid::new_v4()),
+            estimated_rows: best_plan.estimated_rows,
             root_node: best_plan,
-            estimated_cost: CostEstimate::zero(), // Will be calculated from root node
-            estimated_rows: 0,
+            estimated_cost,
             parallelism_degree: 1,
+            cost_profile_version: self.cost_model.profile_version(),
         })
     }
 
+    fn total_cost(node: &PlanNode) -> CostEstimate {
+        node.children.iter().fold(node.estimated_cost.clone(), |cost, child| cost.add(&Self::total_cost(child)))
+    }
+
     fn generate_scan_alternatives(&self, table: &str) -> Result<Vec<PlanNode>, PlanGeneratorError> {
         let mut alternatives = Vec::new();
 
@@ -528,4 +546,63 @@ mod tests {
         let requirements = planner.estimate_resource_requirements(&node);
         assert!(requirements.io_operations > 0);
     }
+
+    fn scan_of(plan: &QueryPlan) -> &PlanOperation {
+        let mut node = &plan.root_node;
+        while let Some(child) = node.children.first() {
+            node = child;
+        }
+        &node.operation
+    }
+
+    #[test]
+    fn test_expensive_random_io_flips_index_lookup_to_table_scan() {
+        let metadata = TableMetadata {
+            row_count: 100_000,
+            column_info: HashMap::from([(
+                "email".to_string(),
+                ColumnInfo {
+                    data_type: "string".to_string(),
+                    nullable: false,
+                    cardinality: 100_000,
+                    selectivity: 0.00001,
+                },
+            )]),
+            available_indexes: vec!["users_email_idx".to_string()],
+        };
+        let query = ParsedQuery {
+            select_columns: vec!["email".to_string()],
+            from_clause: "users".to_string(),
+            where_predicates: vec![],
+            joins: vec![],
+            group_by: vec![],
+            aggregates: vec![],
+            order_by: vec![],
+            limit: None,
+            offset: None,
+        };
+
+        let mut planner = QueryPlanner::new();
+        planner.register_table("users".to_string(), metadata.clone());
+        let plan = planner.generate_plan(&query).unwrap();
+        assert!(matches!(scan_of(&plan), PlanOperation::IndexScan { .. }));
+        assert_eq!(plan.cost_profile_version, None);
+
+        // Random page reads three times the price of sequential ones, as on a spinning disk
+        let mut profile = crate::statistics::CostProfile::from_measurements(crate::statistics::CalibrationMeasurements {
+            page_size: 8192,
+            sequential_page_read_ns: 10_000.0,
+            random_page_read_ns: 30_000.0,
+            comparison_ns: 100.0,
+            hash_probe_ns: 100.0,
+        });
+        profile.version = 2;
+
+        let mut planner = QueryPlanner::new().with_cost_model(CostModel::from_profile(&profile));
+        planner.register_table("users".to_string(), metadata);
+        let plan = planner.generate_plan(&query).unwrap();
+        assert!(matches!(scan_of(&plan), PlanOperation::TableScan { .. }));
+        assert_eq!(plan.cost_profile_version, Some(2));
+        assert!(plan.estimated_cost.total_cost > 0.0);
+    }
 }
@@ -29,15 +29,21 @@
 //! - Compression and serialization
 //! - Metrics and monitoring
 
+use crate::compaction::scheduler::{COMPACTION_STATUS_FILE, CompactionIo, CompactionScheduler, CompactionSchedulerConfig, CompactionSource, WriteLatencyMonitor};
+use crate::compaction::strategy::{CompactionTask, DeadSpaceStrategy, SegmentUsage};
+use crate::fs::{FileMetadata, FileType};
 use crate::state::mpt::{MPTError, Node, NodeId, TrieResult};
 use crate::storage_engine::{DatabaseId, StorageConfig, StorageError, VersionId};
 use parking_lot::RwLock;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs::{File, OpenOptions};
 use std::io::prelude::*;
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Instant, SystemTime};
 
 /// Database operation types for monitoring
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -63,6 +69,13 @@ pub struct DbConfig {
     pub batch_size: usize,
     /// Enable metrics collection
     pub enable_metrics: bool,
+    /// Size at which the append log of a file-backed database starts a new segment
+    pub segment_size: u64,
+    /// Background compaction of a file-backed database's segments, `None` to only compact on request
+    ///
+    /// The scheduler publishes its status to [`COMPACTION_STATUS_FILE`] in the database
+    /// directory unless the configuration names another path.
+    pub compaction: Option<CompactionSchedulerConfig>,
 }
 
 impl Default for DbConfig {
@@ -73,6 +86,8 @@ impl Default for DbConfig {
             cache_size: 10000,
             batch_size: 1000,
             enable_metrics: true,
+            segment_size: 64 * 1024 * 1024,
+            compaction: Some(CompactionSchedulerConfig::for_storage(&StorageConfig::default())),
         }
     }
 }
@@ -97,6 +112,9 @@ pub enum DbError {
 
     #[error("Cache error: {0}")]
     Cache(String),
+
+    #[error("Memory budget of {budget} bytes exceeded: the write needs {required} bytes")]
+    MemoryBudgetExceeded { budget: usize, required: usize },
 }
 
 impl From<DbError> for MPTError {
@@ -121,17 +139,35 @@ trait StorageBackend: Send + Sync {
     fn delete(&self, key: &[u8]) -> DbResult<bool>;
     fn contains(&self, key: &[u8]) -> DbResult<bool>;
     fn flush(&self) -> DbResult<()>;
+
+    fn segment_usage(&self) -> Vec<SegmentUsage> {
+        Vec::new()
+    }
+
+    fn space_usage(&self, matches: &dyn Fn(&[u8]) -> bool) -> SpaceUsage;
+
+    fn compact(&self, _strategy: &DeadSpaceStrategy) -> DbResult<SegmentCompaction> {
+        Ok(SegmentCompaction::default())
+    }
 }
 
 /// In-memory storage backend
+///
+/// Keys and values count against an optional memory budget; a write that would take the
+/// stored bytes past it fails and leaves the stored data as it was.
 struct InMemoryStorage {
     data: Arc<RwLock<HashMap<Vec<u8>, Vec<u8>>>>,
+    /// Bytes of all stored keys and values, updated under the data lock
+    used: AtomicUsize,
+    budget: Option<usize>,
 }
 
 impl InMemoryStorage {
-    fn new() -> Self {
+    fn new(budget: Option<usize>) -> Self {
         Self {
             data: Arc::new(RwLock::new(HashMap::new())),
+            used: AtomicUsize::new(0),
+            budget,
         }
     }
 }
@@ -144,13 +180,23 @@ impl StorageBackend for InMemoryStorage {
 
     fn put(&self, key: Vec<u8>, value: Vec<u8>) -> DbResult<()> {
         let mut data = self.data.write();
+        let replaced = data.get(&key).map_or(0, |old| key.len() + old.len());
+        let required = self.used.load(Ordering::Relaxed) - replaced + key.len() + value.len();
+        if let Some(budget) = self.budget.filter(|&budget| required > budget) {
+            return Err(DbError::MemoryBudgetExceeded { budget, required });
+        }
+        self.used.store(required, Ordering::Relaxed);
         data.insert(key, value);
         Ok(())
     }
 
     fn delete(&self, key: &[u8]) -> DbResult<bool> {
         let mut data = self.data.write();
-        Ok(data.remove(key).is_some())
+        let Some(old) = data.remove(key) else {
+            return Ok(false);
+        };
+        self.used.fetch_sub(key.len() + old.len(), Ordering::Relaxed);
+        Ok(true)
     }
 
     fn contains(&self, key: &[u8]) -> DbResult<bool> {
@@ -158,150 +204,499 @@ impl StorageBackend for InMemoryStorage {
         Ok(data.contains_key(key))
     }
 
+    fn space_usage(&self, matches: &dyn Fn(&[u8]) -> bool) -> SpaceUsage {
+        let data = self.data.read();
+        let mut usage = SpaceUsage::default();
+        for value in data.iter().filter(|(key, _)| matches(key)).map(|(_, value)| value) {
+            usage.live_entries += 1;
+            usage.live_bytes += value.len() as u64;
+        }
+        usage
+    }
+
     fn flush(&self) -> DbResult<()> {
         Ok(())
     }
 }
 
-/// File-based storage backend using a simple key-value file format
-struct FileStorage {
-    data_file: Arc<RwLock<PathBuf>>,
-    index: Arc<RwLock<HashMap<Vec<u8>, (u64, u32)>>>, // key -> (offset, length)
+/// Storage key, in hex, mapped to its location in the index file
+type IndexEntries = HashMap<String, (u32, u64, u32)>;
+
+/// Where a value lives in the append log
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    segment: u32,
+    offset: u64,
+    len: u32,
 }
 
-impl FileStorage {
-    fn new<P: AsRef<Path>>(path: P) -> DbResult<Self> {
-        let data_file = path.as_ref().join("data.db");
-        let index_file = path.as_ref().join("index.db");
+/// Index file contents: the location of every live key and the overwritten or
+/// deleted values still taking up space in each segment
+#[derive(Serialize, Deserialize)]
+struct IndexFile {
+    active: u32,
+    entries: IndexEntries,
+    dead: BTreeMap<u32, Vec<(String, u32)>>,
+}
 
-        // Ensure directory exists
-        if let Some(parent) = data_file.parent() {
-            std::fs::create_dir_all(parent).map_err(|e| DbError::Storage(StorageError::Io(e)))?;
-        }
+/// In-memory state of the append log
+struct Segments {
+    dir: PathBuf,
+    segment_size: u64,
+    /// Segment new values are appended to
+    active: u32,
+    /// Length of every segment file
+    lengths: BTreeMap<u32, u64>,
+    index: HashMap<Vec<u8>, Location>,
+    /// Key and length of every dead value, by segment
+    dead: BTreeMap<u32, Vec<(Vec<u8>, u32)>>,
+}
 
-        let storage = Self {
-            data_file: Arc::new(RwLock::new(data_file.clone())),
-            index: Arc::new(RwLock::new(HashMap::new())),
-        };
+impl Segments {
+    /// Segment 0 keeps the name of the single data file used before the log was segmented
+    fn segment_path(&self, segment: u32) -> PathBuf {
+        if segment == 0 { self.dir.join("data.db") } else { self.dir.join(format!("data.{segment:06}.db")) }
+    }
 
-        // Load existing index if it exists
-        if index_file.exists() {
-            storage.load_index(&index_file)?;
+    fn index_path(&self) -> PathBuf {
+        self.dir.join("index.db")
+    }
+
+    /// Record that the value at `location` of `key` is no longer reachable
+    fn mark_dead(&mut self, key: Vec<u8>, location: Location) {
+        self.dead.entry(location.segment).or_default().push((key, location.len));
+    }
+
+    /// Append a value to the active segment, starting a new one once it is full
+    fn append(&mut self, value: &[u8]) -> DbResult<Location> {
+        let active_len = self.lengths.get(&self.active).copied().unwrap_or(0);
+        if active_len > 0 && active_len + value.len() as u64 > self.segment_size {
+            self.active = self.lengths.keys().next_back().map_or(0, |last| last + 1).max(self.active + 1);
         }
 
-        Ok(storage)
+        let path = self.segment_path(self.active);
+        let mut file = OpenOptions::new().create(true).append(true).open(&path).map_err(|e| DbError::Storage(StorageError::Io(e)))?;
+        let offset = file.seek(SeekFrom::End(0)).map_err(|e| DbError::Storage(StorageError::Io(e)))?;
+        file.write_all(value).map_err(|e| DbError::Storage(StorageError::Io(e)))?;
+        file.flush().map_err(|e| DbError::Storage(StorageError::Io(e)))?;
+
+        self.lengths.insert(self.active, offset + value.len() as u64);
+        Ok(Location {
+            segment: self.active,
+            offset,
+            len: value.len() as u32,
+        })
     }
 
-    fn load_index(&self, index_file: &Path) -> DbResult<()> {
-        let mut file = File::open(index_file).map_err(|e| DbError::Storage(StorageError::Io(e)))?;
-        let mut buffer = String::new();
-        file.read_to_string(&mut buffer).map_err(|e| DbError::Storage(StorageError::Io(e)))?;
+    fn read(&self, location: Location) -> DbResult<Vec<u8>> {
+        let mut file = File::open(self.segment_path(location.segment)).map_err(|e| DbError::Storage(StorageError::Io(e)))?;
+        file.seek(SeekFrom::Start(location.offset)).map_err(|e| DbError::Storage(StorageError::Io(e)))?;
+        let mut buffer = vec![0u8; location.len as usize];
+        file.read_exact(&mut buffer).map_err(|e| DbError::Storage(StorageError::Io(e)))?;
+        Ok(buffer)
+    }
 
-        // Convert HashMap<Vec<u8>, (u64, u32)> to HashMap<String, (u64, u32)> for JSON serialization
-        match serde_json::from_str::<HashMap<String, (u64, u32)>>(&buffer) {
-            Ok(string_index) => {
-                let mut index = self.index.write();
-                index.clear();
-                for (key_str, value) in string_index {
-                    let key_bytes = hex::decode(key_str).unwrap_or_default();
-                    index.insert(key_bytes, value);
-                }
-                Ok(())
-            }
-            Err(_) => {
-                // If we can't deserialize, start with empty index
-                Ok(())
-            }
+    fn usage(&self) -> Vec<SegmentUsage> {
+        let mut usage: BTreeMap<u32, SegmentUsage> = self
+            .lengths
+            .keys()
+            .map(|&segment| {
+                let dead = self.dead.get(&segment).map_or(0, Vec::len);
+                (
+                    segment,
+                    SegmentUsage {
+                        segment,
+                        dead_entries: dead as u64,
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        for location in self.index.values() {
+            let segment = usage.entry(location.segment).or_default();
+            segment.live_entries += 1;
+            segment.live_bytes += location.len as u64;
         }
+        // Whatever the live values do not account for is dead, including values
+        // written before dead entries were tracked
+        for (segment, usage) in &mut usage {
+            usage.segment = *segment;
+            usage.dead_bytes = self.lengths.get(segment).copied().unwrap_or(0).saturating_sub(usage.live_bytes);
+        }
+        usage.into_values().collect()
     }
 
     fn save_index(&self) -> DbResult<()> {
-        let data_file_path = self.data_file.read();
-        let index_file = data_file_path.parent().unwrap().join("index.db");
+        let index = IndexFile {
+            active: self.active,
+            entries: self.index.iter().map(|(key, location)| (hex::encode(key), (location.segment, location.offset, location.len))).collect(),
+            dead: self
+                .dead
+                .iter()
+                .map(|(segment, entries)| (*segment, entries.iter().map(|(key, len)| (hex::encode(key), *len)).collect()))
+                .collect(),
+        };
+        let serialized = serde_json::to_vec(&index).map_err(|e| DbError::Serialization(e.to_string()))?;
 
+        // Write then rename so a crash never leaves a partial index
+        let index_file = self.index_path();
+        let tmp = index_file.with_extension("db.tmp");
         let mut file = OpenOptions::new()
             .write(true)
             .create(true)
             .truncate(true)
-            .open(&index_file)
+            .open(&tmp)
             .map_err(|e| DbError::Storage(StorageError::Io(e)))?;
-
-        // Convert HashMap<Vec<u8>, (u64, u32)> to HashMap<String, (u64, u32)> for JSON serialization
-        let index = self.index.read();
-        let string_index: HashMap<String, (u64, u32)> = index.iter().map(|(k, v)| (hex::encode(k), *v)).collect();
-
-        let serialized = serde_json::to_string(&string_index).map_err(|e| DbError::Serialization(e.to_string()))?;
-
-        file.write_all(serialized.as_bytes()).map_err(|e| DbError::Storage(StorageError::Io(e)))?;
+        file.write_all(&serialized).map_err(|e| DbError::Storage(StorageError::Io(e)))?;
         file.flush().map_err(|e| DbError::Storage(StorageError::Io(e)))?;
+        std::fs::rename(&tmp, &index_file).map_err(|e| DbError::Storage(StorageError::Io(e)))?;
 
         Ok(())
     }
 }
 
-impl StorageBackend for FileStorage {
-    fn get(&self, key: &[u8]) -> DbResult<Option<Vec<u8>>> {
-        let index = self.index.read();
-        if let Some(&(offset, length)) = index.get(key) {
-            drop(index);
+/// File-based storage backend writing values to an append log split into segments
+///
+/// The index of key locations is kept in memory and written to `index.db` after
+/// every change. Overwritten and deleted values stay in their segment, counted as
+/// dead, until [`compact`](StorageBackend::compact) rewrites the segment or the
+/// background compaction scheduler merges it with others.
+struct FileStorage {
+    segments: RwLock<Segments>,
+    /// Receives the latency of every write once a compaction scheduler runs
+    write_latency: OnceLock<Arc<WriteLatencyMonitor>>,
+}
 
-            let data_file = self.data_file.read();
-            let mut file = File::open(&*data_file).map_err(|e| DbError::Storage(StorageError::Io(e)))?;
-            file.seek(SeekFrom::Start(offset)).map_err(|e| DbError::Storage(StorageError::Io(e)))?;
+impl FileStorage {
+    fn new<P: AsRef<Path>>(path: P, segment_size: u64) -> DbResult<Self> {
+        let dir = path.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir).map_err(|e| DbError::Storage(StorageError::Io(e)))?;
+
+        let mut segments = Segments {
+            dir,
+            segment_size: segment_size.max(1),
+            active: 0,
+            lengths: BTreeMap::new(),
+            index: HashMap::new(),
+            dead: BTreeMap::new(),
+        };
 
-            let mut buffer = vec![0u8; length as usize];
-            file.read_exact(&mut buffer).map_err(|e| DbError::Storage(StorageError::Io(e)))?;
+        // Load existing index if it exists
+        let index_file = segments.index_path();
+        if index_file.exists() {
+            Self::load_index(&mut segments, &index_file)?;
+        }
 
-            Ok(Some(buffer))
+        let mut known: Vec<u32> = segments.index.values().map(|location| location.segment).chain(segments.dead.keys().copied()).collect();
+        known.push(segments.active);
+        for segment in known {
+            if let Ok(metadata) = std::fs::metadata(segments.segment_path(segment)) {
+                segments.lengths.insert(segment, metadata.len());
+            }
+        }
+
+        Ok(Self {
+            segments: RwLock::new(segments),
+            write_latency: OnceLock::new(),
+        })
+    }
+
+    fn record_write(&self, started: Instant) {
+        if let Some(monitor) = self.write_latency.get() {
+            monitor.record(started.elapsed());
+        }
+    }
+
+    /// Sealed segments holding dead values, the ones worth merging
+    fn compaction_candidates(&self) -> Vec<FileMetadata> {
+        let segments = self.segments.read();
+        segments
+            .usage()
+            .into_iter()
+            .filter(|usage| usage.segment != segments.active && usage.dead_bytes > 0)
+            .map(|usage| {
+                let path = segments.segment_path(usage.segment);
+                let created_at = std::fs::metadata(&path).and_then(|metadata| metadata.modified()).unwrap_or_else(|_| SystemTime::now());
+                FileMetadata {
+                    id: usage.segment as u64,
+                    file_type: FileType::Data,
+                    version: 1,
+                    size: segments.lengths.get(&usage.segment).copied().unwrap_or(0),
+                    created_at,
+                    path,
+                }
+            })
+            .collect()
+    }
+
+    /// Copy the live values of the task's segments into one new segment and drop the inputs
+    ///
+    /// Values are copied without holding the index lock, so writers carry on meanwhile.
+    /// A value overwritten or deleted during the copy stays behind in the new segment as
+    /// dead space instead of replacing the newer value.
+    fn merge_segments(&self, task: &CompactionTask, io: &CompactionIo<'_>) -> io::Result<Option<FileMetadata>> {
+        let inputs: Vec<u32> = task.input_files.iter().map(|file| file.id as u32).collect();
+        let (output, output_path, mut live) = {
+            let mut segments = self.segments.write();
+            let output = segments.lengths.keys().next_back().map_or(0, |last| last + 1).max(segments.active + 1);
+            // Reserved, so appends starting a new segment skip it
+            segments.lengths.insert(output, 0);
+            let live: Vec<(Vec<u8>, Location)> = segments
+                .index
+                .iter()
+                .filter(|(_, location)| inputs.contains(&location.segment))
+                .map(|(key, location)| (key.clone(), *location))
+                .collect();
+            (output, segments.segment_path(output), live)
+        };
+        live.sort_by_key(|(_, location)| (location.segment, location.offset));
+
+        let result = Self::copy_values(&live, &output_path, io, |segment| self.segments.read().segment_path(segment));
+        let moved = match result {
+            Ok(moved) => moved,
+            Err(e) => {
+                self.segments.write().lengths.remove(&output);
+                let _ = std::fs::remove_file(&output_path);
+                return Err(e);
+            }
+        };
+        let size = moved.last().map_or(0, |(_, from, offset)| offset + from.len as u64);
+
+        let mut segments = self.segments.write();
+        if moved.is_empty() {
+            segments.lengths.remove(&output);
+            std::fs::remove_file(&output_path)?;
         } else {
-            Ok(None)
+            segments.lengths.insert(output, size);
+        }
+        for (key, from, offset) in moved {
+            let to = Location {
+                segment: output,
+                offset,
+                len: from.len,
+            };
+            match segments.index.get(&key) {
+                Some(current) if current.segment == from.segment && current.offset == from.offset => {
+                    segments.index.insert(key, to);
+                }
+                _ => segments.mark_dead(key, to),
+            }
+        }
+        for segment in &inputs {
+            segments.dead.remove(segment);
         }
+        // The new segment is durable and indexed before the inputs go
+        segments.save_index().map_err(io::Error::other)?;
+        for segment in inputs {
+            segments.lengths.remove(&segment);
+            match std::fs::remove_file(segments.segment_path(segment)) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok((size > 0).then(|| FileMetadata {
+            id: output as u64,
+            file_type: FileType::Data,
+            version: 1,
+            size,
+            created_at: SystemTime::now(),
+            path: output_path,
+        }))
     }
 
-    fn put(&self, key: Vec<u8>, value: Vec<u8>) -> DbResult<()> {
-        let data_file = self.data_file.read().clone();
-        drop(self.data_file.read());
+    /// Write the values at `live` one after another to a new file at `output_path`,
+    /// returning each key with its old location and its offset in the new file
+    fn copy_values(live: &[(Vec<u8>, Location)], output_path: &Path, io: &CompactionIo<'_>, path_of: impl Fn(u32) -> PathBuf) -> io::Result<Vec<(Vec<u8>, Location, u64)>> {
+        let mut output = OpenOptions::new().write(true).create(true).truncate(true).open(output_path)?;
+        let mut moved = Vec::with_capacity(live.len());
+        let mut input: Option<(u32, File)> = None;
+        let mut offset = 0u64;
+        let mut value = Vec::new();
+        for (key, location) in live {
+            let file = match &mut input {
+                Some((segment, file)) if *segment == location.segment => file,
+                _ => &mut input.insert((location.segment, File::open(path_of(location.segment))?)).1,
+            };
+            file.seek(SeekFrom::Start(location.offset))?;
+            value.resize(location.len as usize, 0);
+            io.read_exact(file, &mut value)?;
+            io.write(&mut output, &value)?;
+
+            moved.push((key.clone(), *location, offset));
+            offset += location.len as u64;
+        }
+        output.sync_all()?;
+        Ok(moved)
+    }
 
-        // Append to data file
-        let mut file = OpenOptions::new().create(true).append(true).open(&data_file).map_err(|e| DbError::Storage(StorageError::Io(e)))?;
+    fn load_index(segments: &mut Segments, index_file: &Path) -> DbResult<()> {
+        let mut file = File::open(index_file).map_err(|e| DbError::Storage(StorageError::Io(e)))?;
+        let mut buffer = String::new();
+        file.read_to_string(&mut buffer).map_err(|e| DbError::Storage(StorageError::Io(e)))?;
 
-        let offset = file.seek(SeekFrom::End(0)).map_err(|e| DbError::Storage(StorageError::Io(e)))?;
-        file.write_all(&value).map_err(|e| DbError::Storage(StorageError::Io(e)))?;
-        file.flush().map_err(|e| DbError::Storage(StorageError::Io(e)))?;
+        let decode = |key: String| hex::decode(key).unwrap_or_default();
+        if let Ok(index) = serde_json::from_str::<IndexFile>(&buffer) {
+            segments.active = index.active;
+            segments.index = index
+                .entries
+                .into_iter()
+                .map(|(key, (segment, offset, len))| (decode(key), Location { segment, offset, len }))
+                .collect();
+            segments.dead = index
+                .dead
+                .into_iter()
+                .map(|(segment, entries)| (segment, entries.into_iter().map(|(key, len)| (decode(key), len)).collect()))
+                .collect();
+        } else if let Ok(legacy) = serde_json::from_str::<HashMap<String, (u64, u32)>>(&buffer) {
+            // Written before the log was segmented: everything lives in `data.db`
+            segments.index = legacy.into_iter().map(|(key, (offset, len))| (decode(key), Location { segment: 0, offset, len })).collect();
+        }
+        // If we can't deserialize, start with empty index
+        Ok(())
+    }
+}
 
-        // Update index
-        {
-            let mut index = self.index.write();
-            index.insert(key, (offset, value.len() as u32));
+/// Feeds the segments of a [`FileStorage`] to the background compaction scheduler
+struct SegmentCompactionSource {
+    storage: Arc<FileStorage>,
+}
+
+impl CompactionSource for SegmentCompactionSource {
+    fn candidate_files(&self) -> io::Result<Vec<FileMetadata>> {
+        Ok(self.storage.compaction_candidates())
+    }
+
+    fn compact(&self, task: &CompactionTask, io: &CompactionIo<'_>) -> io::Result<Vec<FileMetadata>> {
+        self.storage.merge_segments(task, io).map(|output| output.into_iter().collect())
+    }
+}
+
+impl StorageBackend for FileStorage {
+    fn get(&self, key: &[u8]) -> DbResult<Option<Vec<u8>>> {
+        let segments = self.segments.read();
+        match segments.index.get(key) {
+            Some(&location) => segments.read(location).map(Some),
+            None => Ok(None),
         }
+    }
 
-        // Save index to disk immediately
-        self.save_index()?;
+    fn put(&self, key: Vec<u8>, value: Vec<u8>) -> DbResult<()> {
+        let started = Instant::now();
+        let mut segments = self.segments.write();
+        let location = segments.append(&value)?;
+        if let Some(previous) = segments.index.insert(key.clone(), location) {
+            segments.mark_dead(key, previous);
+        }
 
+        // Save index to disk immediately
+        segments.save_index()?;
+        drop(segments);
+        self.record_write(started);
         Ok(())
     }
 
     fn delete(&self, key: &[u8]) -> DbResult<bool> {
-        let existed = {
-            let mut index = self.index.write();
-            index.remove(key).is_some()
+        let started = Instant::now();
+        let mut segments = self.segments.write();
+        let Some(previous) = segments.index.remove(key) else {
+            return Ok(false);
         };
+        segments.mark_dead(key.to_vec(), previous);
 
-        if existed {
-            // Save index to disk immediately
-            self.save_index()?;
-        }
-
-        Ok(existed)
+        // Save index to disk immediately
+        segments.save_index()?;
+        drop(segments);
+        self.record_write(started);
+        Ok(true)
     }
 
     fn contains(&self, key: &[u8]) -> DbResult<bool> {
-        let index = self.index.read();
-        Ok(index.contains_key(key))
+        Ok(self.segments.read().index.contains_key(key))
     }
 
     fn flush(&self) -> DbResult<()> {
-        self.save_index()
+        self.segments.read().save_index()
+    }
+
+    fn segment_usage(&self) -> Vec<SegmentUsage> {
+        self.segments.read().usage()
+    }
+
+    fn space_usage(&self, matches: &dyn Fn(&[u8]) -> bool) -> SpaceUsage {
+        let segments = self.segments.read();
+        let mut usage = SpaceUsage::default();
+        for (key, location) in &segments.index {
+            if matches(key) {
+                usage.live_entries += 1;
+                usage.live_bytes += location.len as u64;
+            }
+        }
+        for (key, len) in segments.dead.values().flatten() {
+            if matches(key) {
+                usage.dead_entries += 1;
+                usage.dead_bytes += *len as u64;
+            }
+        }
+        usage
+    }
+
+    fn compact(&self, strategy: &DeadSpaceStrategy) -> DbResult<SegmentCompaction> {
+        // Writers wait for the compaction; readers only for the switch to the new index
+        let mut segments = self.segments.write();
+        let usage = segments.usage();
+        let selected = strategy.select(&usage);
+        if selected.is_empty() {
+            return Ok(SegmentCompaction::default());
+        }
+
+        // Live values move to fresh segments so the selected ones can be removed whole
+        let first_output = segments.lengths.keys().next_back().map_or(0, |last| last + 1);
+        segments.active = first_output;
+        let mut report = SegmentCompaction::default();
+        for &segment in &selected {
+            let stats = usage.iter().find(|stats| stats.segment == segment).cloned().unwrap_or_default();
+            let mut live: Vec<(Vec<u8>, Location)> = segments
+                .index
+                .iter()
+                .filter(|(_, location)| location.segment == segment)
+                .map(|(key, location)| (key.clone(), *location))
+                .collect();
+            live.sort_by_key(|(_, location)| location.offset);
+
+            for (key, location) in live {
+                let value = segments.read(location)?;
+                let moved = segments.append(&value)?;
+                segments.index.insert(key, moved);
+            }
+
+            report.segments_compacted += 1;
+            report.dead_entries_dropped += stats.dead_entries;
+            report.bytes_reclaimed += stats.dead_bytes;
+            report.bytes_rewritten += stats.live_bytes;
+        }
+        for segment in &selected {
+            segments.dead.remove(segment);
+        }
+
+        // Outputs are durable and indexed before the inputs go
+        for segment in segments.lengths.range(first_output..).map(|(segment, _)| *segment).collect::<Vec<_>>() {
+            if let Ok(file) = File::open(segments.segment_path(segment)) {
+                file.sync_all().map_err(|e| DbError::Storage(StorageError::Io(e)))?;
+            }
+        }
+        segments.save_index()?;
+        for segment in selected {
+            segments.lengths.remove(&segment);
+            match std::fs::remove_file(segments.segment_path(segment)) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(DbError::Storage(StorageError::Io(e))),
+            }
+        }
+
+        Ok(report)
     }
 }
 
@@ -325,6 +720,27 @@ pub struct DbStats {
     pub compression_ratio: f64,
 }
 
+/// Live and dead space taken by a set of keys
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpaceUsage {
+    pub live_entries: u64,
+    pub live_bytes: u64,
+    /// Overwritten and deleted values of the keys not yet compacted away
+    pub dead_entries: u64,
+    pub dead_bytes: u64,
+}
+
+/// Outcome of rewriting segments to drop their dead entries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SegmentCompaction {
+    pub segments_compacted: u64,
+    pub dead_entries_dropped: u64,
+    /// Disk space freed
+    pub bytes_reclaimed: u64,
+    /// Live data copied to new segments
+    pub bytes_rewritten: u64,
+}
+
 /// High-level database interface trait
 ///
 /// This trait provides the core operations needed by the MPT implementation
@@ -333,6 +749,11 @@ pub trait DatabaseInterface: Send + Sync {
     /// Get a value by key
     fn get(&self, key: &[u8]) -> DbResult<Option<Vec<u8>>>;
 
+    /// Get a value read once by a scan, without caching it in place of the hot keys
+    fn get_for_scan(&self, key: &[u8]) -> DbResult<Option<Vec<u8>>> {
+        self.get(key)
+    }
+
     /// Put a key-value pair
     fn put(&self, key: Vec<u8>, value: Vec<u8>) -> DbResult<()>;
 
@@ -356,6 +777,21 @@ pub trait DatabaseInterface: Send + Sync {
 
     /// Close the database connection
     fn close(&mut self) -> DbResult<()>;
+
+    /// Live and dead space of each storage segment, oldest first
+    fn segment_usage(&self) -> Vec<SegmentUsage> {
+        Vec::new()
+    }
+
+    /// Live and dead space taken by the keys `matches` accepts
+    fn space_usage(&self, _matches: &dyn Fn(&[u8]) -> bool) -> SpaceUsage {
+        SpaceUsage::default()
+    }
+
+    /// Rewrite the segments `strategy` picks without their dead entries
+    fn compact(&self, _strategy: &DeadSpaceStrategy) -> DbResult<SegmentCompaction> {
+        Ok(SegmentCompaction::default())
+    }
 }
 
 /// Database snapshot interface for point-in-time reads
@@ -386,6 +822,9 @@ pub struct Database {
 
     /// Storage backend (either in-memory or file-based)
     storage: Arc<dyn StorageBackend>,
+
+    /// Background compaction of the file-based backend's segments
+    compaction: Option<CompactionScheduler>,
 }
 
 impl Database {
@@ -393,22 +832,53 @@ impl Database {
     pub fn new<P: AsRef<Path>>(path: P, config: DbConfig) -> DbResult<Self> {
         let cache = Arc::new(RwLock::new(HashMap::with_capacity(config.cache_size)));
         let stats = Arc::new(RwLock::new(DbStats::default()));
-        let storage: Arc<dyn StorageBackend> = Arc::new(FileStorage::new(path)?);
+        let file_storage = Arc::new(FileStorage::new(&path, config.segment_size)?);
+
+        let compaction = match &config.compaction {
+            Some(compaction_config) => {
+                let mut compaction_config = compaction_config.clone();
+                compaction_config.status_path.get_or_insert_with(|| path.as_ref().join(COMPACTION_STATUS_FILE));
+                let source = Arc::new(SegmentCompactionSource { storage: Arc::clone(&file_storage) });
+                let mut scheduler = CompactionScheduler::new(compaction_config, source);
+                let _ = file_storage.write_latency.set(scheduler.latency_monitor());
+                scheduler.start().map_err(|e| DbError::Storage(StorageError::Io(e)))?;
+                Some(scheduler)
+            }
+            None => None,
+        };
 
         Ok(Self {
             config,
             cache,
             stats,
             db_id: DatabaseId(1),
-            storage,
+            storage: file_storage,
+            compaction,
         })
     }
 
+    /// Background compaction scheduler of a file-backed database, if it runs one
+    pub fn compaction(&self) -> Option<&CompactionScheduler> {
+        self.compaction.as_ref()
+    }
+
     /// Create a new in-memory database for testing
     pub fn new_in_memory() -> DbResult<Self> {
+        Self::in_memory(None)
+    }
+
+    /// Create an in-memory database whose stored keys and values may take at most `budget` bytes
+    ///
+    /// Writes past the budget fail with [`DbError::MemoryBudgetExceeded`]. The read cache is
+    /// not counted.
+    pub fn new_in_memory_with_budget(budget: usize) -> DbResult<Self> {
+        Self::in_memory(Some(budget))
+    }
+
+    fn in_memory(budget: Option<usize>) -> DbResult<Self> {
         let cache = Arc::new(RwLock::new(HashMap::with_capacity(DbConfig::default().cache_size)));
         let stats = Arc::new(RwLock::new(DbStats::default()));
-        let storage: Arc<dyn StorageBackend> = Arc::new(InMemoryStorage::new());
+        let storage: Arc<dyn StorageBackend> = Arc::new(InMemoryStorage::new(budget));
 
         Ok(Self {
             config: DbConfig::default(),
@@ -416,6 +886,7 @@ impl Database {
             stats,
             db_id: DatabaseId(1),
             storage,
+            compaction: None,
         })
     }
 
@@ -500,15 +971,25 @@ impl DatabaseInterface for Database {
         }
     }
 
+    fn get_for_scan(&self, key: &[u8]) -> DbResult<Option<Vec<u8>>> {
+        if let Some(cached_value) = self.check_cache(key) {
+            self.update_stats(DbOperation::Get, true);
+            return Ok(Some(cached_value));
+        }
+
+        // A miss is served from storage and left out of the cache
+        let value = self.storage.get(key)?;
+        self.update_stats(DbOperation::Get, false);
+        Ok(value)
+    }
+
     fn put(&self, key: Vec<u8>, value: Vec<u8>) -> DbResult<()> {
         // Serialize and compress if needed
         let compressed_value = self.serialize_with_compression(&value)?;
 
-        // Update cache
-        self.update_cache(key.clone(), value);
-
-        // Write to storage
-        self.storage.put(key, compressed_value)?;
+        // Write to storage, then cache what was accepted
+        self.storage.put(key.clone(), compressed_value)?;
+        self.update_cache(key, value);
 
         // Flush immediately to ensure persistence
         self.storage.flush()?;
@@ -577,8 +1058,22 @@ impl DatabaseInterface for Database {
     }
 
     fn close(&mut self) -> DbResult<()> {
-        // In real implementation, this would close the storage engine
-        Ok(())
+        if let Some(compaction) = &mut self.compaction {
+            compaction.stop();
+        }
+        self.storage.flush()
+    }
+
+    fn segment_usage(&self) -> Vec<SegmentUsage> {
+        self.storage.segment_usage()
+    }
+
+    fn space_usage(&self, matches: &dyn Fn(&[u8]) -> bool) -> SpaceUsage {
+        self.storage.space_usage(matches)
+    }
+
+    fn compact(&self, strategy: &DeadSpaceStrategy) -> DbResult<SegmentCompaction> {
+        self.storage.compact(strategy)
     }
 }
 
@@ -605,11 +1100,16 @@ impl DatabaseSnapshot for DatabaseSnapshotImpl {
 /// Storage adapter that implements NodeStorage for MPT
 pub struct MptStorageAdapter {
     db: Arc<dyn DatabaseInterface>,
+    /// Nodes read through this adapter and its clones
+    reads: Arc<AtomicU64>,
 }
 
 impl MptStorageAdapter {
     pub fn new(db: Arc<dyn DatabaseInterface>) -> Self {
-        Self { db }
+        Self {
+            db,
+            reads: Arc::new(AtomicU64::new(0)),
+        }
     }
 
     /// Serialize a node for storage
@@ -630,13 +1130,17 @@ impl MptStorageAdapter {
 
 impl Clone for MptStorageAdapter {
     fn clone(&self) -> Self {
-        Self { db: self.db.clone() }
+        Self {
+            db: self.db.clone(),
+            reads: self.reads.clone(),
+        }
     }
 }
 
 impl crate::state::mpt::trie::NodeStorage for MptStorageAdapter {
     fn get_node(&self, id: &NodeId) -> TrieResult<Option<Node>> {
         let key = self.node_key(id);
+        self.reads.fetch_add(1, Ordering::Relaxed);
         match self.db.get(&key) {
             Ok(Some(data)) => {
                 let node = self.deserialize_node(&data)?;
@@ -663,6 +1167,10 @@ impl crate::state::mpt::trie::NodeStorage for MptStorageAdapter {
         let key = self.node_key(id);
         self.db.contains(&key).unwrap_or(false)
     }
+
+    fn node_reads(&self) -> u64 {
+        self.reads.load(Ordering::Relaxed)
+    }
 }
 
 /// Helper function to create a persistent MPT with database backend
@@ -671,7 +1179,7 @@ pub fn create_persistent_mpt<P: AsRef<Path>>(db_path: P, config: Option<DbConfig
     let database = Arc::new(Database::new(db_path, config)?);
     let storage_adapter = MptStorageAdapter::new(database);
 
-    Ok(crate::state::mpt::MerklePatriciaTrie::new(storage_adapter))
+    Ok(crate::state::mpt::MerklePatriciaTrie::new(storage_adapter).with_node_cache(crate::state::mpt::DEFAULT_NODE_CACHE_BYTES))
 }
 
 /// Helper function to create an in-memory MPT with database backend for testing
@@ -687,6 +1195,7 @@ mod tests {
     use super::*;
     use crate::state::mpt::trie::NodeStorage;
     use crate::state::mpt::{Key, Value};
+    use std::time::Duration;
     use tempfile::TempDir;
 
     #[test]
@@ -712,6 +1221,21 @@ mod tests {
         assert!(!db.contains(&key).unwrap());
     }
 
+    #[test]
+    fn test_in_memory_budget() {
+        let db = Database::new_in_memory_with_budget(64).unwrap();
+        db.put(b"a".to_vec(), vec![0; 40]).unwrap();
+
+        // Overwriting a value only counts the difference
+        db.put(b"a".to_vec(), vec![1; 50]).unwrap();
+        assert!(matches!(db.put(b"b".to_vec(), vec![0; 20]), Err(DbError::MemoryBudgetExceeded { budget: 64, required: 72 })));
+        assert_eq!(db.get(b"b").unwrap(), None);
+
+        assert!(db.delete(b"a").unwrap());
+        db.put(b"b".to_vec(), vec![0; 20]).unwrap();
+        assert_eq!(db.get(b"b").unwrap(), Some(vec![0; 20]));
+    }
+
     #[test]
     fn test_batch_operations() {
         let db = Database::new_in_memory().unwrap();
@@ -754,6 +1278,39 @@ mod tests {
         assert!(stats_after.cache_hits > stats_before.cache_hits);
     }
 
+    #[test]
+    fn test_scan_leaves_hot_keys_cached() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = DbConfig {
+            cache_size: 4,
+            compaction: None,
+            ..DbConfig::default()
+        };
+        let db = Database::new(temp_dir.path(), config.clone()).unwrap();
+        for i in 0..20 {
+            db.put(format!("key{i:02}").into_bytes(), vec![b'x'; 16]).unwrap();
+        }
+
+        // Reopen with an empty cache, then warm it with the hot keys
+        drop(db);
+        let db = Database::new(temp_dir.path(), config).unwrap();
+        let hot = [b"key00".to_vec(), b"key01".to_vec()];
+        for key in &hot {
+            db.get(key).unwrap();
+        }
+
+        for i in 0..20 {
+            assert!(db.get_for_scan(format!("key{i:02}").as_bytes()).unwrap().is_some());
+        }
+
+        // The scan is larger than the cache, yet every hot key is still a hit
+        let hits_before = db.stats().cache_hits;
+        for key in &hot {
+            db.get(key).unwrap();
+        }
+        assert_eq!(db.stats().cache_hits, hits_before + 2);
+    }
+
     #[test]
     fn test_snapshot_functionality() {
         let db = Database::new_in_memory().unwrap();
@@ -841,4 +1398,105 @@ mod tests {
         assert!(stats.get_count > 0);
         assert!(stats.delete_count > 0);
     }
+
+    #[test]
+    fn test_segment_accounting_and_compaction() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = DbConfig {
+            segment_size: 1024,
+            compaction: None,
+            ..DbConfig::default()
+        };
+        let db = Database::new(temp_dir.path(), config.clone()).unwrap();
+        for i in 0..100 {
+            db.put(format!("key{i:03}").into_bytes(), vec![b'x'; 100]).unwrap();
+        }
+        db.put(b"key000".to_vec(), vec![b'y'; 100]).unwrap();
+        for i in 1..90 {
+            db.delete(format!("key{i:03}").as_bytes()).unwrap();
+        }
+
+        let usage = db.segment_usage();
+        assert!(usage.len() > 5);
+        assert_eq!(usage.iter().map(|segment| segment.live_entries).sum::<u64>(), 11);
+        assert_eq!(usage.iter().map(|segment| segment.dead_entries).sum::<u64>(), 90);
+        assert_eq!(usage.iter().map(|segment| segment.dead_bytes).sum::<u64>(), 9000);
+        let keys = db.space_usage(&|key: &[u8]| key.starts_with(b"key00"));
+        assert_eq!((keys.live_entries, keys.dead_entries), (1, 10));
+
+        let report = db.compact(&DeadSpaceStrategy::default()).unwrap();
+        assert_eq!(report.bytes_reclaimed, 9000);
+        assert_eq!(report.dead_entries_dropped, 90);
+        let on_disk: u64 = std::fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("data"))
+            .map(|entry| entry.metadata().unwrap().len())
+            .sum();
+        assert_eq!(on_disk, 1100);
+
+        // Moved values survive a reopen
+        drop(db);
+        let db = Database::new(temp_dir.path(), config).unwrap();
+        assert_eq!(db.get(b"key000").unwrap(), Some(vec![b'y'; 100]));
+        assert_eq!(db.get(b"key099").unwrap(), Some(vec![b'x'; 100]));
+        assert_eq!(db.get(b"key050").unwrap(), None);
+        assert!(db.segment_usage().iter().all(|segment| segment.dead_bytes == 0));
+    }
+
+    #[test]
+    fn test_background_compaction_merges_segments() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = DbConfig {
+            segment_size: 1024,
+            enable_compression: false,
+            compaction: Some(CompactionSchedulerConfig {
+                check_interval: Duration::from_millis(50),
+                io_budget_bytes_per_sec: 0,
+                ..CompactionSchedulerConfig::default()
+            }),
+            ..DbConfig::default()
+        };
+        let db = Database::new(temp_dir.path(), config.clone()).unwrap();
+        for i in 0..100 {
+            db.put(format!("key{i:03}").into_bytes(), vec![b'x'; 100]).unwrap();
+        }
+        for i in 1..90 {
+            db.delete(format!("key{i:03}").as_bytes()).unwrap();
+        }
+
+        let compaction = db.compaction().unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while compaction.stats().completed_tasks == 0 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        let stats = compaction.stats();
+        assert!(stats.completed_tasks > 0);
+        assert!(stats.write_latency_p99_us.is_some());
+
+        // Only live values were copied, so the merged segments hold no dead space
+        let usage = db.segment_usage();
+        assert_eq!(usage.iter().map(|segment| segment.live_entries).sum::<u64>(), 11);
+        assert!(usage.iter().map(|segment| segment.dead_bytes).sum::<u64>() < 8900);
+        assert!(temp_dir.path().join(COMPACTION_STATUS_FILE).exists());
+
+        drop(db);
+        let db = Database::new(temp_dir.path(), DbConfig { compaction: None, ..config }).unwrap();
+        assert_eq!(db.get(b"key000").unwrap(), Some(vec![b'x'; 100]));
+        assert_eq!(db.get(b"key095").unwrap(), Some(vec![b'x'; 100]));
+        assert_eq!(db.get(b"key050").unwrap(), None);
+    }
+
+    #[test]
+    fn test_loads_unsegmented_index() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("data.db"), b"stalevalue").unwrap();
+        let legacy: HashMap<String, (u64, u32)> = [(hex::encode(b"key"), (5, 5))].into_iter().collect();
+        std::fs::write(temp_dir.path().join("index.db"), serde_json::to_vec(&legacy).unwrap()).unwrap();
+
+        let db = Database::new(temp_dir.path(), DbConfig::default()).unwrap();
+        assert_eq!(db.get(b"key").unwrap(), Some(b"value".to_vec()));
+        let usage = db.segment_usage();
+        assert_eq!((usage[0].live_bytes, usage[0].dead_bytes), (5, 5));
+    }
 }
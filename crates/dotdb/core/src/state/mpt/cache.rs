@@ -0,0 +1,204 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Decoded trie nodes kept in memory
+//!
+//! Nodes are content-addressed: a node's ID is the hash of what it holds, so a
+//! cached node can never go stale. Rewriting a node during an insert produces a
+//! node with a new ID rather than changing the old one, and the old entry simply
+//! ages out. The cache is bounded by the approximate in-memory size of the nodes
+//! it holds and evicts the least recently used node first.
+
+use crate::state::mpt::lib::NodeId;
+use crate::state::mpt::node::Node;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// Memory the node cache may hold when not configured otherwise
+pub const DEFAULT_NODE_CACHE_BYTES: usize = 64 * 1024 * 1024;
+
+/// Node cache activity
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NodeCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    /// Approximate memory held by the cached nodes
+    pub bytes: usize,
+    pub entries: usize,
+    pub capacity_bytes: usize,
+}
+
+impl NodeCacheStats {
+    /// Share of lookups served from the cache, zero before the first lookup
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 { 0.0 } else { self.hits as f64 / lookups as f64 }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CachedNode {
+    node: Arc<Node>,
+    bytes: usize,
+    last_used: u64,
+}
+
+/// LRU of decoded nodes keyed by their ID, bounded by bytes
+#[derive(Debug, Clone)]
+pub struct NodeCache {
+    capacity_bytes: usize,
+    nodes: HashMap<NodeId, CachedNode>,
+    /// Node IDs by the tick they were last used at, oldest first
+    recency: BTreeMap<u64, NodeId>,
+    tick: u64,
+    stats: NodeCacheStats,
+}
+
+impl NodeCache {
+    pub fn new(capacity_bytes: usize) -> Self {
+        Self {
+            capacity_bytes,
+            nodes: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            stats: NodeCacheStats {
+                capacity_bytes,
+                ..NodeCacheStats::default()
+            },
+        }
+    }
+
+    /// The node with `id`, marking it recently used
+    pub fn get(&mut self, id: &NodeId) -> Option<Arc<Node>> {
+        self.tick += 1;
+        let Some(cached) = self.nodes.get_mut(id) else {
+            self.stats.misses += 1;
+            return None;
+        };
+        self.recency.remove(&cached.last_used);
+        cached.last_used = self.tick;
+        self.recency.insert(self.tick, *id);
+        self.stats.hits += 1;
+        Some(cached.node.clone())
+    }
+
+    pub fn contains(&self, id: &NodeId) -> bool {
+        self.nodes.contains_key(id)
+    }
+
+    /// Cache `node`, evicting the least recently used nodes to stay within the budget.
+    /// A node larger than the whole budget is not cached.
+    pub fn insert(&mut self, node: Arc<Node>) {
+        let bytes = node_bytes(&node);
+        if bytes > self.capacity_bytes {
+            return;
+        }
+
+        self.remove(&node.id);
+        while self.stats.bytes + bytes > self.capacity_bytes {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            if let Some(evicted) = self.nodes.remove(&oldest) {
+                self.stats.bytes -= evicted.bytes;
+                self.stats.evictions += 1;
+            }
+        }
+
+        self.tick += 1;
+        self.recency.insert(self.tick, node.id);
+        self.nodes.insert(node.id, CachedNode { node, bytes, last_used: self.tick });
+        self.stats.bytes += bytes;
+        self.stats.entries = self.nodes.len();
+    }
+
+    /// Drop the node with `id`, as when it is deleted from storage
+    pub fn remove(&mut self, id: &NodeId) {
+        if let Some(cached) = self.nodes.remove(id) {
+            self.recency.remove(&cached.last_used);
+            self.stats.bytes -= cached.bytes;
+            self.stats.entries = self.nodes.len();
+        }
+    }
+
+    /// Drop every node, keeping the counters
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.recency.clear();
+        self.stats.bytes = 0;
+        self.stats.entries = 0;
+    }
+
+    pub fn stats(&self) -> NodeCacheStats {
+        self.stats
+    }
+}
+
+/// Approximate memory a cached node takes: the node itself, its paths and values, and
+/// the cache's bookkeeping for it
+fn node_bytes(node: &Node) -> usize {
+    std::mem::size_of::<Node>() + std::mem::size_of::<CachedNode>() + std::mem::size_of::<NodeId>() * 2 + node.size_bytes() as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::mpt::lib::CompactPath;
+
+    fn leaf(value: u8) -> Arc<Node> {
+        Arc::new(Node::new_leaf(CompactPath::new(vec![1, 2], true), vec![value; 16]))
+    }
+
+    #[test]
+    fn test_least_recently_used_node_is_evicted() {
+        let (a, b, c) = (leaf(1), leaf(2), leaf(3));
+        let mut cache = NodeCache::new(node_bytes(&a) * 2);
+
+        cache.insert(a.clone());
+        cache.insert(b.clone());
+        // Using `a` leaves `b` as the least recently used
+        assert!(cache.get(&a.id).is_some());
+        cache.insert(c.clone());
+
+        assert!(cache.contains(&a.id) && cache.contains(&c.id));
+        assert!(!cache.contains(&b.id));
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.evictions, stats.entries), (1, 1, 2));
+        assert_eq!(stats.bytes, node_bytes(&a) * 2);
+        assert!(cache.get(&b.id).is_none());
+        assert_eq!(cache.stats().misses, 1);
+    }
+
+    #[test]
+    fn test_reinserting_a_node_does_not_count_it_twice() {
+        let a = leaf(1);
+        let mut cache = NodeCache::new(DEFAULT_NODE_CACHE_BYTES);
+        cache.insert(a.clone());
+        cache.insert(a.clone());
+        assert_eq!((cache.stats().entries, cache.stats().bytes), (1, node_bytes(&a)));
+
+        cache.remove(&a.id);
+        assert_eq!((cache.stats().entries, cache.stats().bytes), (0, 0));
+    }
+
+    #[test]
+    fn test_node_over_budget_is_not_cached() {
+        let mut cache = NodeCache::new(16);
+        cache.insert(leaf(1));
+        assert_eq!(cache.stats().entries, 0);
+    }
+}
@@ -0,0 +1,264 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Structural diff between two roots of the same trie
+//!
+//! The walk descends both tries in lockstep, one nibble at a time. A position
+//! inside a trie is a node plus how many nibbles of its path have already been
+//! consumed, so leaves and extensions with long paths line up with branches in
+//! the other trie. Whenever both sides reach the same node at the same offset
+//! the subtree is identical and is skipped without being read, so the cost is
+//! proportional to the size of the change rather than the size of the state.
+//!
+//! Changes are produced in key order, which makes the walk resumable: pass the
+//! last key of a page as `start_after` to continue.
+
+use crate::state::mpt::lib::{Hash, Key, MPTError, NodeId, TrieResult, Value, key_to_nibbles, nibbles_to_key};
+use crate::state::mpt::node::NodeType;
+use crate::state::mpt::trie::NodeStorage;
+
+/// A key whose value differs between two roots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrieChange {
+    Added { key: Key, value: Value },
+    Removed { key: Key, value: Value },
+    Modified { key: Key, old: Value, new: Value },
+}
+
+impl TrieChange {
+    pub fn key(&self) -> &Key {
+        match self {
+            TrieChange::Added { key, .. } | TrieChange::Removed { key, .. } | TrieChange::Modified { key, .. } => key,
+        }
+    }
+}
+
+/// Which part of a diff to produce
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptions {
+    /// Only report keys sorting after this one
+    pub start_after: Option<Key>,
+    /// Stop after this many changes, 0 for no limit
+    pub limit: usize,
+}
+
+/// One page of changes, in key order
+#[derive(Debug, Clone, Default)]
+pub struct TrieDiff {
+    pub changes: Vec<TrieChange>,
+    /// Whether changes remain after the last one returned
+    pub has_more: bool,
+}
+
+/// A position inside a trie: a node and how many nibbles of its path are consumed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cursor {
+    node: NodeId,
+    offset: usize,
+}
+
+/// A cursor expanded to the value stored exactly at it and the cursors one nibble below it
+struct Level {
+    value: Option<Value>,
+    children: [Option<Cursor>; 16],
+}
+
+struct DiffWalker<'a, S: NodeStorage> {
+    storage: &'a S,
+    start_after: Option<Vec<u8>>,
+    limit: usize,
+    diff: TrieDiff,
+}
+
+/// Diff the trie stored in `storage` between `from` and `to`
+pub fn diff_roots<S: NodeStorage>(storage: &S, from: Hash, to: Hash, options: &DiffOptions) -> TrieResult<TrieDiff> {
+    let mut walker = DiffWalker {
+        storage,
+        start_after: options.start_after.as_deref().map(key_to_nibbles),
+        limit: options.limit,
+        diff: TrieDiff::default(),
+    };
+    let root = |node| Some(Cursor { node, offset: 0 });
+    walker.walk(&mut Vec::new(), root(from), root(to))?;
+    Ok(walker.diff)
+}
+
+impl<S: NodeStorage> DiffWalker<'_, S> {
+    fn walk(&mut self, prefix: &mut Vec<u8>, from: Option<Cursor>, to: Option<Cursor>) -> TrieResult<()> {
+        if from == to || self.diff.has_more || self.before_start(prefix) {
+            return Ok(());
+        }
+
+        let from = from.map(|c| self.level(c)).transpose()?;
+        let to = to.map(|c| self.level(c)).transpose()?;
+        let (from_value, from_children) = split(from);
+        let (to_value, to_children) = split(to);
+
+        let change = match (from_value, to_value) {
+            (None, Some(value)) => Some(TrieChange::Added { key: nibbles_to_key(prefix), value }),
+            (Some(value), None) => Some(TrieChange::Removed { key: nibbles_to_key(prefix), value }),
+            (Some(old), Some(new)) if old != new => Some(TrieChange::Modified {
+                key: nibbles_to_key(prefix),
+                old,
+                new,
+            }),
+            _ => None,
+        };
+        if let Some(change) = change {
+            self.emit(prefix, change);
+        }
+
+        for nibble in 0..16 {
+            prefix.push(nibble as u8);
+            let result = self.walk(prefix, from_children[nibble], to_children[nibble]);
+            prefix.pop();
+            result?;
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, prefix: &[u8], change: TrieChange) {
+        if self.start_after.as_deref().is_some_and(|after| prefix <= after) {
+            return;
+        }
+        if self.limit != 0 && self.diff.changes.len() == self.limit {
+            self.diff.has_more = true;
+            return;
+        }
+        self.diff.changes.push(change);
+    }
+
+    /// Whether every key below `prefix` sorts at or before `start_after`
+    fn before_start(&self, prefix: &[u8]) -> bool {
+        let Some(after) = &self.start_after else { return false };
+        let shared = prefix.len().min(after.len());
+        prefix[..shared] < after[..shared]
+    }
+
+    fn level(&self, cursor: Cursor) -> TrieResult<Level> {
+        let node = self.storage.get_node(&cursor.node)?.ok_or(MPTError::NodeNotFound(cursor.node))?;
+        let mut level = Level { value: None, children: [None; 16] };
+
+        match node.node_type {
+            NodeType::Empty => {}
+            NodeType::Leaf { path, value } => match path.nibbles.get(cursor.offset) {
+                None => level.value = Some(value),
+                Some(&nibble) => level.children[nibble as usize] = Some(Cursor { offset: cursor.offset + 1, ..cursor }),
+            },
+            NodeType::Extension { path, child } => match path.nibbles.get(cursor.offset) {
+                None => return self.level(Cursor { node: child, offset: 0 }),
+                Some(&nibble) => level.children[nibble as usize] = Some(Cursor { offset: cursor.offset + 1, ..cursor }),
+            },
+            NodeType::Branch { children, value } => {
+                level.value = value;
+                for (slot, child) in level.children.iter_mut().zip(children) {
+                    *slot = child.map(|node| Cursor { node, offset: 0 });
+                }
+            }
+        }
+
+        Ok(level)
+    }
+}
+
+fn split(level: Option<Level>) -> (Option<Value>, [Option<Cursor>; 16]) {
+    match level {
+        Some(level) => (level.value, level.children),
+        None => (None, [None; 16]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::mpt::MerklePatriciaTrie;
+
+    fn put(trie: &mut MerklePatriciaTrie<crate::state::mpt::trie::InMemoryStorage>, pairs: &[(&str, &str)]) -> Hash {
+        for (key, value) in pairs {
+            trie.put(key.as_bytes().to_vec(), value.as_bytes().to_vec()).unwrap();
+        }
+        trie.root_hash()
+    }
+
+    #[test]
+    fn test_identical_roots_have_no_changes() {
+        let mut trie = MerklePatriciaTrie::new_in_memory();
+        let root = put(&mut trie, &[("a", "1"), ("b", "2")]);
+        let diff = trie.diff(root, root, &DiffOptions::default()).unwrap();
+        assert!(diff.changes.is_empty());
+        assert!(!diff.has_more);
+    }
+
+    #[test]
+    fn test_added_removed_and_modified_keys() {
+        let mut trie = MerklePatriciaTrie::new_in_memory();
+        let from = put(&mut trie, &[("apple", "1"), ("apricot", "2"), ("banana", "3")]);
+        trie.delete(&b"banana".to_vec()).unwrap();
+        let to = put(&mut trie, &[("apple", "10"), ("avocado", "4")]);
+
+        let diff = trie.diff(from, to, &DiffOptions::default()).unwrap();
+        assert_eq!(
+            diff.changes,
+            vec![
+                TrieChange::Modified {
+                    key: b"apple".to_vec(),
+                    old: b"1".to_vec(),
+                    new: b"10".to_vec(),
+                },
+                TrieChange::Added {
+                    key: b"avocado".to_vec(),
+                    value: b"4".to_vec()
+                },
+                TrieChange::Removed {
+                    key: b"banana".to_vec(),
+                    value: b"3".to_vec()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_from_empty_root_lists_everything() {
+        let mut trie = MerklePatriciaTrie::new_in_memory();
+        let empty = trie.root_hash();
+        let to = put(&mut trie, &[("a", "1"), ("ab", "2"), ("b", "3")]);
+
+        let diff = trie.diff(empty, to, &DiffOptions::default()).unwrap();
+        let keys: Vec<_> = diff.changes.iter().map(|c| c.key().clone()).collect();
+        assert_eq!(keys, vec![b"a".to_vec(), b"ab".to_vec(), b"b".to_vec()]);
+        assert!(diff.changes.iter().all(|c| matches!(c, TrieChange::Added { .. })));
+    }
+
+    #[test]
+    fn test_pagination_resumes_after_last_key() {
+        let mut trie = MerklePatriciaTrie::new_in_memory();
+        let from = trie.root_hash();
+        let to = put(&mut trie, &[("k1", "a"), ("k2", "b"), ("k3", "c")]);
+
+        let first = trie.diff(from, to, &DiffOptions { start_after: None, limit: 2 }).unwrap();
+        assert_eq!(first.changes.len(), 2);
+        assert!(first.has_more);
+
+        let options = DiffOptions {
+            start_after: Some(first.changes[1].key().clone()),
+            limit: 2,
+        };
+        let second = trie.diff(from, to, &options).unwrap();
+        assert_eq!(second.changes.len(), 1);
+        assert_eq!(second.changes[0].key(), &b"k3".to_vec());
+        assert!(!second.has_more);
+    }
+}
@@ -23,6 +23,8 @@
 //! # Module Structure
 //!
 //! - `lib`: Core types, utilities, and error definitions
+//! - `cache`: Size-bounded LRU of decoded nodes
+//! - `diff`: Structural diff between two roots of a trie
 //! - `node`: Trie node implementations and node type definitions
 //! - `proof`: Merkle proof generation and verification
 //! - `trie`: Main trie implementation and operations
@@ -57,6 +59,12 @@
 /// Core types and utilities for the MPT implementation
 pub mod lib;
 
+/// Size-bounded LRU of decoded nodes
+pub mod cache;
+
+/// Structural diff between two trie roots
+pub mod diff;
+
 /// Trie node implementations and node type definitions
 pub mod node;
 
@@ -67,10 +75,12 @@ pub mod proof;
 pub mod trie;
 
 // Re-export commonly used types for convenience
+pub use cache::{DEFAULT_NODE_CACHE_BYTES, NodeCacheStats};
+pub use diff::{DiffOptions, TrieChange, TrieDiff};
 pub use lib::{Hash, Key, NodeId, TrieResult, Value};
 pub use node::{Node, NodeType};
 pub use proof::StateProof;
-pub use trie::MerklePatriciaTrie;
+pub use trie::{MerklePatriciaTrie, ScanOptions, TrieScan, TrieStats};
 
 /// Main error type for MPT operations
 ///
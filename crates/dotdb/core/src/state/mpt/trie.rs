@@ -28,6 +28,7 @@
 //! - Support for all standard trie operations (get, put, delete)
 //! - Proof generation and verification
 //! - Thread-safe operations with RwLock
+//! - Optional node cache, with identical subtrees stored once
 //!
 //! # Performance Considerations
 //!
@@ -37,11 +38,15 @@
 //! - Thread-safe concurrent access
 //! - Efficient proof generation
 
-use crate::state::mpt::lib::{CompactPath, Hash, Key, MPTError, NodeId, TrieResult, Value, common_prefix, key_to_nibbles};
+use crate::state::mpt::cache::{NodeCache, NodeCacheStats};
+use crate::state::mpt::diff::{DiffOptions, TrieDiff, diff_roots};
+use crate::state::mpt::lib::{CompactPath, Hash, Key, MPTError, NodeId, TrieResult, Value, common_prefix, key_to_nibbles, nibbles_to_key};
 use crate::state::mpt::node::{Node, NodeType};
 use crate::state::mpt::proof::{ProofBuilder, StateProof};
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 /// Storage interface for MPT nodes
 ///
@@ -83,6 +88,13 @@ pub trait NodeStorage {
     ///
     /// True if the node exists, false otherwise
     fn contains_node(&self, id: &NodeId) -> bool;
+
+    /// Number of nodes read from this storage so far, for tracing
+    ///
+    /// Backends that do not count reads report zero.
+    fn node_reads(&self) -> u64 {
+        0
+    }
 }
 
 /// In-memory storage implementation for testing
@@ -90,15 +102,19 @@ pub trait NodeStorage {
 /// This implementation stores nodes in a HashMap, making it suitable for testing
 /// and small-scale usage. For production use, consider implementing a persistent
 /// storage backend.
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct InMemoryStorage {
     nodes: HashMap<NodeId, Node>,
+    reads: AtomicU64,
 }
 
 impl InMemoryStorage {
     /// Create a new in-memory storage
     pub fn new() -> Self {
-        Self { nodes: HashMap::new() }
+        Self {
+            nodes: HashMap::new(),
+            reads: AtomicU64::new(0),
+        }
     }
 
     /// Get the number of stored nodes
@@ -123,8 +139,18 @@ impl Default for InMemoryStorage {
     }
 }
 
+impl Clone for InMemoryStorage {
+    fn clone(&self) -> Self {
+        Self {
+            nodes: self.nodes.clone(),
+            reads: AtomicU64::new(self.reads.load(Ordering::Relaxed)),
+        }
+    }
+}
+
 impl NodeStorage for InMemoryStorage {
     fn get_node(&self, id: &NodeId) -> TrieResult<Option<Node>> {
+        self.reads.fetch_add(1, Ordering::Relaxed);
         Ok(self.nodes.get(id).cloned())
     }
 
@@ -141,6 +167,75 @@ impl NodeStorage for InMemoryStorage {
     fn contains_node(&self, id: &NodeId) -> bool {
         self.nodes.contains_key(id)
     }
+
+    fn node_reads(&self) -> u64 {
+        self.reads.load(Ordering::Relaxed)
+    }
+}
+
+/// Storage and node cache activity of a trie
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrieStats {
+    /// Nodes read from storage
+    pub node_reads: u64,
+    /// Nodes written to storage
+    pub node_writes: u64,
+    /// Node writes skipped because storage already held a node with the same hash
+    pub deduplicated_writes: u64,
+    /// Node cache counters, when the trie has a node cache
+    pub cache: Option<NodeCacheStats>,
+}
+
+/// Which entries a scan returns
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    /// Only keys starting with this prefix
+    pub prefix: Key,
+    /// Only keys sorting after this one
+    pub start_after: Option<Key>,
+    /// Stop after this many entries, 0 for no limit
+    pub limit: usize,
+}
+
+/// One page of entries, in key order
+#[derive(Debug, Clone, Default)]
+pub struct TrieScan {
+    pub entries: Vec<(Key, Value)>,
+    /// Whether entries remain after the last one returned
+    pub has_more: bool,
+}
+
+/// Scan bounds in nibbles, and the page collected so far
+struct ScanWalk {
+    prefix: Vec<u8>,
+    start_after: Option<Vec<u8>>,
+    limit: usize,
+    scan: TrieScan,
+}
+
+impl ScanWalk {
+    /// Whether keys starting with `path` can match the prefix and sort after `start_after`
+    fn may_contain(&self, path: &[u8]) -> bool {
+        let common = path.len().min(self.prefix.len());
+        if path[..common] != self.prefix[..common] {
+            return false;
+        }
+        // Below a path sorting before the resume key, and not leading to it, every key sorts before it too
+        self.start_after.as_ref().is_none_or(|after| path > after.as_slice() || after.starts_with(path))
+    }
+
+    /// Adds an entry when it matches, returning false once the page is full
+    fn offer(&mut self, key_nibbles: &[u8], value: &Value) -> bool {
+        if !key_nibbles.starts_with(&self.prefix) || self.start_after.as_ref().is_some_and(|after| key_nibbles <= after.as_slice()) {
+            return true;
+        }
+        if self.limit != 0 && self.scan.entries.len() == self.limit {
+            self.scan.has_more = true;
+            return false;
+        }
+        self.scan.entries.push((nibbles_to_key(key_nibbles), value.clone()));
+        true
+    }
 }
 
 /// Merkle Patricia Trie implementation
@@ -151,6 +246,10 @@ impl NodeStorage for InMemoryStorage {
 pub struct MerklePatriciaTrie<S: NodeStorage> {
     storage: RwLock<S>,
     root: RwLock<NodeId>,
+    /// Decoded nodes served to get, put, delete and proof lookups before storage is asked
+    cache: Option<Mutex<NodeCache>>,
+    node_writes: AtomicU64,
+    deduplicated_writes: AtomicU64,
 }
 
 impl<S: NodeStorage + Clone> Clone for MerklePatriciaTrie<S> {
@@ -158,6 +257,10 @@ impl<S: NodeStorage + Clone> Clone for MerklePatriciaTrie<S> {
         Self {
             storage: RwLock::new(self.storage.read().clone()),
             root: RwLock::new(*self.root.read()),
+            // Cached nodes stay valid for the cloned storage, which holds the same nodes
+            cache: self.cache.as_ref().map(|cache| Mutex::new(cache.lock().clone())),
+            node_writes: AtomicU64::new(self.node_writes.load(Ordering::Relaxed)),
+            deduplicated_writes: AtomicU64::new(self.deduplicated_writes.load(Ordering::Relaxed)),
         }
     }
 }
@@ -179,6 +282,9 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
         let trie = Self {
             storage: RwLock::new(storage),
             root: RwLock::new(root_id),
+            cache: None,
+            node_writes: AtomicU64::new(0),
+            deduplicated_writes: AtomicU64::new(0),
         };
 
         // Store the empty root node
@@ -195,6 +301,59 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
         *self.root.read()
     }
 
+    /// Keep up to `capacity_bytes` of decoded nodes in memory, least recently used evicted first
+    pub fn with_node_cache(mut self, capacity_bytes: usize) -> Self {
+        self.cache = Some(Mutex::new(NodeCache::new(capacity_bytes)));
+        self
+    }
+
+    /// Number of nodes read from storage so far; diff two readings to cost an operation
+    pub fn node_reads(&self) -> u64 {
+        self.storage.read().node_reads()
+    }
+
+    /// Storage reads and writes so far, and the node cache's counters
+    pub fn stats(&self) -> TrieStats {
+        TrieStats {
+            node_reads: self.node_reads(),
+            node_writes: self.node_writes.load(Ordering::Relaxed),
+            deduplicated_writes: self.deduplicated_writes.load(Ordering::Relaxed),
+            cache: self.cache.as_ref().map(|cache| cache.lock().stats()),
+        }
+    }
+
+    /// The node with `node_id`, from the cache when it holds it
+    fn load_node(&self, storage: &S, node_id: NodeId) -> TrieResult<Arc<Node>> {
+        if let Some(cache) = &self.cache
+            && let Some(node) = cache.lock().get(&node_id)
+        {
+            return Ok(node);
+        }
+
+        let node = Arc::new(storage.get_node(&node_id)?.ok_or(MPTError::NodeNotFound(node_id))?);
+        if let Some(cache) = &self.cache {
+            cache.lock().insert(node.clone());
+        }
+        Ok(node)
+    }
+
+    /// Write `node` to storage unless a node with its hash is already there. Nodes are
+    /// content-addressed, so identical subtrees are stored once.
+    fn store_node(&self, node: &Node) -> TrieResult<NodeId> {
+        if self.storage.read().contains_node(&node.id) {
+            self.deduplicated_writes.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.storage.write().put_node(node)?;
+            self.node_writes.fetch_add(1, Ordering::Relaxed);
+        }
+
+        // Nodes just written are usually read again by the next operation on the same path
+        if let Some(cache) = &self.cache {
+            cache.lock().insert(Arc::new(node.clone()));
+        }
+        Ok(node.id)
+    }
+
     /// Get value for a key
     ///
     /// # Arguments
@@ -211,8 +370,14 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
         self.get_recursive(&*storage, root_id, &key_nibbles)
     }
 
+    /// Get the value of a key as of an earlier root, which must still have its nodes in storage
+    pub fn get_at(&self, root: Hash, key: &Key) -> TrieResult<Option<Value>> {
+        let storage = self.storage.read();
+        self.get_recursive(&*storage, root, &key_to_nibbles(key))
+    }
+
     fn get_recursive(&self, storage: &S, node_id: NodeId, key_nibbles: &[u8]) -> TrieResult<Option<Value>> {
-        let node = storage.get_node(&node_id)?.ok_or(MPTError::NodeNotFound(node_id))?;
+        let node = self.load_node(storage, node_id)?;
 
         match &node.node_type {
             NodeType::Empty => Ok(None),
@@ -266,7 +431,7 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
 
     fn put_recursive(&mut self, node_id: NodeId, key_nibbles: &[u8], value: Value) -> TrieResult<NodeId> {
         let storage = self.storage.read();
-        let node = storage.get_node(&node_id)?.ok_or(MPTError::NodeNotFound(node_id))?;
+        let node = self.load_node(&*storage, node_id)?;
         drop(storage);
 
         match &node.node_type {
@@ -274,7 +439,7 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
                 let path = CompactPath::new(key_nibbles.to_vec(), true);
                 let new_node = Node::new_leaf(path, value);
                 let new_id = new_node.id;
-                self.storage.write().put_node(&new_node)?;
+                self.store_node(&new_node)?;
                 Ok(new_id)
             }
 
@@ -283,7 +448,7 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
                     // Update existing leaf
                     let new_node = Node::new_leaf(path.clone(), value);
                     let new_id = new_node.id;
-                    self.storage.write().put_node(&new_node)?;
+                    self.store_node(&new_node)?;
                     Ok(new_id)
                 } else {
                     // Split the leaf
@@ -299,7 +464,7 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
                     let new_child = self.put_recursive(*child, &key_nibbles[common_len..], value)?;
                     let new_node = Node::new_extension(path.clone(), new_child);
                     let new_id = new_node.id;
-                    self.storage.write().put_node(&new_node)?;
+                    self.store_node(&new_node)?;
                     Ok(new_id)
                 } else {
                     // Split the extension
@@ -312,7 +477,7 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
                     // Update branch value
                     let new_node = Node::new_branch(*children, Some(value));
                     let new_id = new_node.id;
-                    self.storage.write().put_node(&new_node)?;
+                    self.store_node(&new_node)?;
                     Ok(new_id)
                 } else {
                     // Continue down a branch
@@ -325,7 +490,7 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
                     let new_child = self.put_recursive(child_id, &key_nibbles[1..], value)?;
                     let new_node = node.with_branch_child(nibble, Some(new_child))?;
                     let new_id = new_node.id;
-                    self.storage.write().put_node(&new_node)?;
+                    self.store_node(&new_node)?;
                     Ok(new_id)
                 }
             }
@@ -346,12 +511,12 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
                     let new_leaf_path = CompactPath::new(new_path[1..].to_vec(), true);
                     let new_leaf = Node::new_leaf(new_leaf_path, new_value);
                     children[nibble] = Some(new_leaf.id);
-                    self.storage.write().put_node(&new_leaf)?;
+                    self.store_node(&new_leaf)?;
                 }
 
                 let branch = Node::new_branch(children, Some(old_value.clone()));
                 let branch_id = branch.id;
-                self.storage.write().put_node(&branch)?;
+                self.store_node(&branch)?;
                 Ok(branch_id)
             } else {
                 let old_nibble = old_path[0] as usize;
@@ -360,16 +525,16 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
                 let old_leaf_path = CompactPath::new(old_path[1..].to_vec(), true);
                 let old_leaf = Node::new_leaf(old_leaf_path, old_value.clone());
                 children[old_nibble] = Some(old_leaf.id);
-                self.storage.write().put_node(&old_leaf)?;
+                self.store_node(&old_leaf)?;
 
                 let new_leaf_path = CompactPath::new(new_path[1..].to_vec(), true);
                 let new_leaf = Node::new_leaf(new_leaf_path, new_value);
                 children[new_nibble] = Some(new_leaf.id);
-                self.storage.write().put_node(&new_leaf)?;
+                self.store_node(&new_leaf)?;
 
                 let branch = Node::new_branch(children, None);
                 let branch_id = branch.id;
-                self.storage.write().put_node(&branch)?;
+                self.store_node(&branch)?;
                 Ok(branch_id)
             }
         } else {
@@ -379,7 +544,7 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
 
             let extension = Node::new_extension(common_path, branch_id);
             let extension_id = extension.id;
-            self.storage.write().put_node(&extension)?;
+            self.store_node(&extension)?;
             Ok(extension_id)
         }
     }
@@ -396,7 +561,7 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
             let remaining_path = CompactPath::new(path.nibbles[common_len + 1..].to_vec(), false);
             let new_extension = Node::new_extension(remaining_path, child);
             children[path.nibbles[common_len] as usize] = Some(new_extension.id);
-            self.storage.write().put_node(&new_extension)?;
+            self.store_node(&new_extension)?;
         }
 
         // Handle the new key
@@ -404,7 +569,7 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
             // New key ends here, becomes branch value
             let branch = Node::new_branch(children, Some(value));
             let branch_id = branch.id;
-            self.storage.write().put_node(&branch)?;
+            self.store_node(&branch)?;
 
             if common_len == 0 {
                 Ok(branch_id)
@@ -412,7 +577,7 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
                 let common_path = CompactPath::new(key_nibbles[..common_len].to_vec(), false);
                 let extension = Node::new_extension(common_path, branch_id);
                 let extension_id = extension.id;
-                self.storage.write().put_node(&extension)?;
+                self.store_node(&extension)?;
                 Ok(extension_id)
             }
         } else {
@@ -421,11 +586,11 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
             let new_leaf_path = CompactPath::new(remaining_key.to_vec(), true);
             let new_leaf = Node::new_leaf(new_leaf_path, value);
             children[key_nibbles[common_len] as usize] = Some(new_leaf.id);
-            self.storage.write().put_node(&new_leaf)?;
+            self.store_node(&new_leaf)?;
 
             let branch = Node::new_branch(children, None);
             let branch_id = branch.id;
-            self.storage.write().put_node(&branch)?;
+            self.store_node(&branch)?;
 
             if common_len == 0 {
                 Ok(branch_id)
@@ -433,7 +598,7 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
                 let common_path = CompactPath::new(key_nibbles[..common_len].to_vec(), false);
                 let extension = Node::new_extension(common_path, branch_id);
                 let extension_id = extension.id;
-                self.storage.write().put_node(&extension)?;
+                self.store_node(&extension)?;
                 Ok(extension_id)
             }
         }
@@ -455,7 +620,7 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
 
     fn delete_recursive(&mut self, node_id: NodeId, key_nibbles: &[u8]) -> TrieResult<Option<NodeId>> {
         let storage = self.storage.read();
-        let node = storage.get_node(&node_id)?.ok_or(MPTError::NodeNotFound(node_id))?;
+        let node = self.load_node(&*storage, node_id)?;
         drop(storage);
 
         match &node.node_type {
@@ -478,7 +643,7 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
                     Some(new_child) => {
                         let new_node = Node::new_extension(path.clone(), new_child);
                         let new_id = new_node.id;
-                        self.storage.write().put_node(&new_node)?;
+                        self.store_node(&new_node)?;
                         Ok(Some(new_id))
                     }
                     None => Ok(None),
@@ -490,7 +655,7 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
                     if value.is_some() {
                         let new_node = Node::new_branch(*children, None);
                         let new_id = new_node.id;
-                        self.storage.write().put_node(&new_node)?;
+                        self.store_node(&new_node)?;
                         Ok(Some(new_id))
                     } else {
                         Ok(None)
@@ -506,13 +671,13 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
                             Some(new_child) => {
                                 let new_node = node.with_branch_child(nibble, Some(new_child))?;
                                 let new_id = new_node.id;
-                                self.storage.write().put_node(&new_node)?;
+                                self.store_node(&new_node)?;
                                 Ok(Some(new_id))
                             }
                             None => {
                                 let new_node = node.with_branch_child(nibble, None)?;
                                 let new_id = new_node.id;
-                                self.storage.write().put_node(&new_node)?;
+                                self.store_node(&new_node)?;
                                 Ok(Some(new_id))
                             }
                         },
@@ -535,7 +700,7 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
     }
 
     fn get_proof_recursive(&self, storage: &S, node_id: NodeId, key_nibbles: &[u8], proof_builder: &mut ProofBuilder) -> TrieResult<Option<Value>> {
-        let node = storage.get_node(&node_id)?.ok_or(MPTError::NodeNotFound(node_id))?;
+        let node = self.load_node(storage, node_id)?;
 
         proof_builder.add_node(&node);
 
@@ -599,7 +764,6 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
     }
 
     fn collect_keys_recursive(&self, storage: &S, node_id: NodeId, prefix: Vec<u8>, keys: &mut Vec<Key>) -> TrieResult<()> {
-        use crate::state::mpt::lib::nibbles_to_key;
         let node = storage.get_node(&node_id)?.ok_or(MPTError::NodeNotFound(node_id))?;
         match &node.node_type {
             NodeType::Empty => Ok(()),
@@ -631,6 +795,59 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
         }
     }
 
+    /// Entries under a root in key order
+    ///
+    /// Subtrees that hold no key matching the prefix, or only keys up to
+    /// `start_after`, are skipped without being read.
+    pub fn scan(&self, root: Hash, options: &ScanOptions) -> TrieResult<TrieScan> {
+        let storage = self.storage.read();
+        let mut walk = ScanWalk {
+            prefix: key_to_nibbles(&options.prefix),
+            start_after: options.start_after.as_deref().map(key_to_nibbles),
+            limit: options.limit,
+            scan: TrieScan::default(),
+        };
+        self.scan_recursive(&*storage, root, &mut Vec::new(), &mut walk)?;
+        Ok(walk.scan)
+    }
+
+    /// Returns false once the page is full
+    fn scan_recursive(&self, storage: &S, node_id: NodeId, path: &mut Vec<u8>, walk: &mut ScanWalk) -> TrieResult<bool> {
+        if !walk.may_contain(path) {
+            return Ok(true);
+        }
+
+        let node = self.load_node(storage, node_id)?;
+        let depth = path.len();
+        let keep_going = match &node.node_type {
+            NodeType::Empty => true,
+            NodeType::Leaf { path: rest, value } => {
+                path.extend_from_slice(&rest.nibbles);
+                walk.offer(path, value)
+            }
+            NodeType::Extension { path: rest, child } => {
+                path.extend_from_slice(&rest.nibbles);
+                self.scan_recursive(storage, *child, path, walk)?
+            }
+            NodeType::Branch { children, value } => {
+                // A value stored at the branch sorts before everything below it
+                let mut keep_going = value.as_ref().is_none_or(|value| walk.offer(path, value));
+                for (nibble, child) in children.iter().enumerate() {
+                    let Some(child) = child else { continue };
+                    if !keep_going {
+                        break;
+                    }
+                    path.push(nibble as u8);
+                    keep_going = self.scan_recursive(storage, *child, path, walk)?;
+                    path.pop();
+                }
+                keep_going
+            }
+        };
+        path.truncate(depth);
+        Ok(keep_going)
+    }
+
     /// Get the number of key-value pairs in the trie
     ///
     /// # Returns
@@ -649,6 +866,25 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
         self.root_hash() == Node::new_empty().id
     }
 
+    /// Diff two roots of this trie
+    ///
+    /// Only subtrees whose hashes differ are visited, so both roots must still
+    /// have their nodes in storage.
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The root to diff from
+    /// * `to` - The root to diff to
+    /// * `options` - Resume point and page size
+    ///
+    /// # Returns
+    ///
+    /// The changed keys in key order
+    pub fn diff(&self, from: Hash, to: Hash, options: &DiffOptions) -> TrieResult<TrieDiff> {
+        let storage = self.storage.read();
+        diff_roots(&*storage, from, to, options)
+    }
+
     /// Set the root node of the trie
     ///
     /// # Arguments
@@ -680,10 +916,15 @@ impl<S: NodeStorage> MerklePatriciaTrie<S> {
 
     /// Get mutable access to the underlying storage for direct operations
     ///
+    /// Nodes may be deleted through it, so the node cache is emptied first.
+    ///
     /// # Returns
     ///
     /// A mutable reference to the storage wrapped in RwLock
     pub fn get_storage_mut(&mut self) -> &mut RwLock<S> {
+        if let Some(cache) = &self.cache {
+            cache.lock().clear();
+        }
         &mut self.storage
     }
 }
@@ -702,6 +943,7 @@ impl MerklePatriciaTrie<InMemoryStorage> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::state::mpt::cache::DEFAULT_NODE_CACHE_BYTES;
     use std::collections::HashSet;
     use std::sync::{Arc, Mutex};
 
@@ -737,6 +979,93 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_node_reads_are_counted() {
+        let mut trie = MerklePatriciaTrie::new_in_memory();
+        trie.put(b"key1".to_vec(), b"value1".to_vec()).unwrap();
+        trie.put(b"key2".to_vec(), b"value2".to_vec()).unwrap();
+
+        let before = trie.node_reads();
+        assert_eq!(trie.get(&b"key1".to_vec()).unwrap(), Some(b"value1".to_vec()));
+        let lookup = trie.node_reads() - before;
+        assert!(lookup >= 2, "a lookup below the root reads at least two nodes, read {lookup}");
+
+        // Clones carry the count over
+        assert_eq!(trie.clone().node_reads(), trie.node_reads());
+    }
+
+    #[test]
+    fn test_node_cache_serves_repeated_lookups() {
+        let mut trie = MerklePatriciaTrie::new_in_memory().with_node_cache(DEFAULT_NODE_CACHE_BYTES);
+        for i in 0..64u32 {
+            trie.put(i.to_be_bytes().to_vec(), format!("value{i}").into_bytes()).unwrap();
+        }
+
+        let before = trie.node_reads();
+        for i in 0..64u32 {
+            assert_eq!(trie.get(&i.to_be_bytes().to_vec()).unwrap(), Some(format!("value{i}").into_bytes()));
+        }
+        // Every node on those paths was written through the cache by the inserts
+        assert_eq!(trie.node_reads(), before);
+
+        let cache = trie.stats().cache.unwrap();
+        assert!(cache.hits > 0 && cache.bytes > 0 && cache.entries > 0);
+        assert_eq!(MerklePatriciaTrie::new_in_memory().stats().cache, None);
+    }
+
+    #[test]
+    fn test_cached_proofs_match_uncached_proofs() {
+        let mut uncached = MerklePatriciaTrie::new_in_memory();
+        // A budget of a few nodes keeps evicting, so proofs mix cached and stored nodes
+        let mut cached = MerklePatriciaTrie::new_in_memory().with_node_cache(2048);
+        for i in 0..200u32 {
+            let (key, value) = (format!("key{i}").into_bytes(), format!("value{}", i % 7).into_bytes());
+            uncached.put(key.clone(), value.clone()).unwrap();
+            cached.put(key, value).unwrap();
+        }
+        for i in (0..200u32).step_by(3) {
+            uncached.delete(&format!("key{i}").into_bytes()).unwrap();
+            cached.delete(&format!("key{i}").into_bytes()).unwrap();
+        }
+        assert_eq!(cached.root_hash(), uncached.root_hash());
+
+        for i in 0..210u32 {
+            let key = format!("key{i}").into_bytes();
+            let proof = cached.get_proof(&key).unwrap();
+            assert_eq!(proof, uncached.get_proof(&key).unwrap());
+            assert_eq!(cached.verify_proof(&proof).unwrap(), uncached.verify_proof(&proof).unwrap());
+            if proof.value.is_some() {
+                assert!(cached.verify_proof(&proof).unwrap());
+            }
+        }
+        assert!(cached.stats().cache.unwrap().evictions > 0);
+    }
+
+    #[test]
+    fn test_identical_nodes_are_stored_once() {
+        let mut trie = MerklePatriciaTrie::new_in_memory();
+        trie.put(b"key1".to_vec(), b"value1".to_vec()).unwrap();
+        trie.put(b"key2".to_vec(), b"value2".to_vec()).unwrap();
+        let stats = trie.stats();
+
+        // Rewriting a value rebuilds the same nodes along its path
+        trie.put(b"key1".to_vec(), b"value1".to_vec()).unwrap();
+        let after = trie.stats();
+        assert_eq!(after.node_writes, stats.node_writes);
+        assert!(after.deduplicated_writes > stats.deduplicated_writes);
+    }
+
+    #[test]
+    fn test_direct_storage_access_clears_node_cache() {
+        let mut trie = MerklePatriciaTrie::new_in_memory().with_node_cache(DEFAULT_NODE_CACHE_BYTES);
+        trie.put(b"key1".to_vec(), b"value1".to_vec()).unwrap();
+        assert!(trie.stats().cache.unwrap().entries > 0);
+
+        trie.get_storage_mut();
+        assert_eq!(trie.stats().cache.unwrap().entries, 0);
+        assert_eq!(trie.get(&b"key1".to_vec()).unwrap(), Some(b"value1".to_vec()));
+    }
+
     #[test]
     fn test_key_update() {
         let mut trie = MerklePatriciaTrie::new_in_memory();
@@ -843,6 +1172,56 @@ mod tests {
         assert_eq!(keys, expected_keys);
     }
 
+    #[test]
+    fn test_scan_pages_in_key_order() {
+        let mut trie = MerklePatriciaTrie::new_in_memory();
+        for key in ["user", "user:1", "user:2", "config", "user:10", "users"] {
+            trie.put(key.as_bytes().to_vec(), key.as_bytes().to_vec()).unwrap();
+        }
+        let root = trie.root_hash();
+        let keys = |scan: &TrieScan| scan.entries.iter().map(|(key, _)| String::from_utf8(key.clone()).unwrap()).collect::<Vec<_>>();
+
+        let all = trie.scan(root, &ScanOptions::default()).unwrap();
+        assert_eq!(keys(&all), ["config", "user", "user:1", "user:10", "user:2", "users"]);
+        assert!(!all.has_more);
+
+        let options = ScanOptions {
+            prefix: b"user:".to_vec(),
+            limit: 2,
+            ..Default::default()
+        };
+        let first = trie.scan(root, &options).unwrap();
+        assert_eq!(keys(&first), ["user:1", "user:10"]);
+        assert!(first.has_more);
+
+        let second = trie
+            .scan(
+                root,
+                &ScanOptions {
+                    start_after: first.entries.last().map(|(key, _)| key.clone()),
+                    ..options
+                },
+            )
+            .unwrap();
+        assert_eq!(keys(&second), ["user:2"]);
+        assert!(!second.has_more);
+    }
+
+    #[test]
+    fn test_reads_at_an_earlier_root() {
+        let mut trie = MerklePatriciaTrie::new_in_memory();
+        trie.put(b"a".to_vec(), b"1".to_vec()).unwrap();
+        let old_root = trie.root_hash();
+        trie.put(b"a".to_vec(), b"2".to_vec()).unwrap();
+        trie.put(b"b".to_vec(), b"3".to_vec()).unwrap();
+
+        assert_eq!(trie.get_at(old_root, &b"a".to_vec()).unwrap(), Some(b"1".to_vec()));
+        assert_eq!(trie.get_at(old_root, &b"b".to_vec()).unwrap(), None);
+        assert_eq!(trie.scan(old_root, &ScanOptions::default()).unwrap().entries.len(), 1);
+        // The current root is left alone
+        assert_eq!(trie.get(&b"a".to_vec()).unwrap(), Some(b"2".to_vec()));
+    }
+
     #[test]
     fn test_concurrent_access() {
         use std::thread;
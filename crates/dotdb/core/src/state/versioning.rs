@@ -308,6 +308,34 @@ impl DotVersionManager {
         Ok(version_id)
     }
 
+    /// Register a version created by an earlier manager, e.g. one read back from storage after a restart
+    ///
+    /// The version becomes current unless a newer one is already loaded for its dot, and
+    /// versions created afterwards are numbered after it.
+    pub fn load_version(&self, version: DotStateVersion) -> Result<(), DotVersioningError> {
+        let dot_address = version.dot_address;
+        let version_id = version.version_id;
+        {
+            let mut counter = self.version_counter.lock().unwrap();
+            *counter = (*counter).max(version_id.logical_version);
+        }
+
+        {
+            let mut versions = self.versions.write().unwrap();
+            let dot_versions = versions.entry(dot_address).or_default();
+            dot_versions.insert(version_id, version);
+            self.cleanup_old_versions_for_dot(dot_versions)?;
+        }
+
+        let mut current_versions = self.current_versions.write().unwrap();
+        let current = current_versions.entry(dot_address).or_insert(version_id);
+        if current.logical_version < version_id.logical_version {
+            *current = version_id;
+        }
+
+        Ok(())
+    }
+
     /// Get a specific version of a dot
     pub fn get_version(&self, dot_address: DotAddress, version_id: StateVersionId) -> Option<DotStateVersion> {
         let versions = self.versions.read().unwrap();
@@ -670,6 +698,23 @@ mod tests {
         [42u8; 32]
     }
 
+    #[test]
+    fn test_loaded_versions_are_current_and_numbered_after() {
+        let manager = DotVersionManager::new(10);
+        let dot_addr = create_test_dot_address();
+        for (number, root) in [(7, [7u8; 32]), (3, [3u8; 32])] {
+            let version = DotStateVersion::new(StateVersionId::new(number, 1000 + number), root, dot_addr, None, "loaded".to_string());
+            manager.load_version(version).unwrap();
+        }
+
+        let current = manager.get_current_version(dot_addr).unwrap();
+        assert_eq!((current.version_id.logical_version(), current.mpt_root_hash), (7, [7u8; 32]));
+        assert_eq!(manager.get_all_versions(dot_addr).len(), 2);
+
+        let next = manager.create_version(dot_addr, create_test_mpt_root(), "next".to_string()).unwrap();
+        assert_eq!(next.logical_version(), 8);
+    }
+
     #[test]
     fn test_state_version_id_creation() {
         let version_id = StateVersionId::new(1, 1000);
@@ -0,0 +1,304 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Cost model calibration
+//!
+//! Times sequential page reads, random page reads, in-memory value comparisons
+//! and hash probes against the storage directory, and turns the timings into the
+//! constants of the query planner's cost model. Costs are relative to one
+//! sequential page read, so a profile only changes plans through the ratios
+//! between the operations.
+//!
+//! Profiles are stored next to the statistics in [`COST_PROFILE_FILE`]. Every
+//! calibration saves a new version and keeps the previous ones, so a plan change
+//! can be traced to the profile the plan was costed with.
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::File;
+use std::hint::black_box;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use super::collector::{StatisticsError, StatisticsResult};
+
+/// File inside a data directory holding the calibrated cost profiles
+pub const COST_PROFILE_FILE: &str = "cost_profile.json";
+
+/// Superseded profiles kept in the profile file
+const PROFILE_HISTORY: usize = 10;
+
+/// Sizes of the micro-benchmarks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalibrationConfig {
+    pub page_size: usize,
+    /// Pages in the scratch file the read benchmarks use
+    pub file_pages: usize,
+    pub random_reads: usize,
+    pub comparisons: usize,
+    pub hash_probes: usize,
+}
+
+impl Default for CalibrationConfig {
+    fn default() -> Self {
+        Self {
+            page_size: 8192,
+            file_pages: 8192,
+            random_reads: 4096,
+            comparisons: 1_000_000,
+            hash_probes: 1_000_000,
+        }
+    }
+}
+
+/// Average time of each benchmarked operation, in nanoseconds
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CalibrationMeasurements {
+    pub page_size: usize,
+    pub sequential_page_read_ns: f64,
+    pub random_page_read_ns: f64,
+    pub comparison_ns: f64,
+    pub hash_probe_ns: f64,
+}
+
+/// Cost model constants, in units of one sequential page read
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CostProfile {
+    /// Assigned when the profile is saved; increases with every calibration
+    pub version: u64,
+    /// Nanoseconds since the Unix epoch
+    pub calibrated_at: u64,
+    pub sequential_page_cost: f64,
+    pub random_page_cost: f64,
+    /// Cost of evaluating a predicate against one row
+    pub cpu_cost_per_row: f64,
+    /// Cost of one hash table lookup, as in hash joins and grouping
+    pub hash_probe_cost: f64,
+    pub measurements: CalibrationMeasurements,
+}
+
+impl CostProfile {
+    /// Profile derived from benchmark timings, not yet versioned
+    pub fn from_measurements(measurements: CalibrationMeasurements) -> Self {
+        let page_ns = measurements.sequential_page_read_ns.max(f64::MIN_POSITIVE);
+        Self {
+            version: 0,
+            calibrated_at: crate::storage_engine::generate_timestamp(),
+            sequential_page_cost: 1.0,
+            random_page_cost: measurements.random_page_read_ns / page_ns,
+            cpu_cost_per_row: measurements.comparison_ns / page_ns,
+            hash_probe_cost: measurements.hash_probe_ns / page_ns,
+            measurements,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PersistedCostProfiles {
+    /// Oldest first; the last one is in use
+    profiles: Vec<CostProfile>,
+}
+
+fn read_profiles(path: &Path) -> StatisticsResult<PersistedCostProfiles> {
+    match std::fs::read(path) {
+        Ok(data) => serde_json::from_slice(&data).map_err(|e| StatisticsError::StorageError(format!("{}: {}", path.display(), e))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(PersistedCostProfiles::default()),
+        Err(e) => Err(StatisticsError::StorageError(e.to_string())),
+    }
+}
+
+/// The profile in use for a data directory, if it was ever calibrated
+pub fn load_cost_profile(data_dir: &Path) -> StatisticsResult<Option<CostProfile>> {
+    Ok(read_profiles(&data_dir.join(COST_PROFILE_FILE))?.profiles.pop())
+}
+
+/// Every saved profile of a data directory, oldest first
+pub fn cost_profile_history(data_dir: &Path) -> StatisticsResult<Vec<CostProfile>> {
+    Ok(read_profiles(&data_dir.join(COST_PROFILE_FILE))?.profiles)
+}
+
+/// Save `profile` as the next version for a data directory and return it as saved
+pub fn save_cost_profile(data_dir: &Path, mut profile: CostProfile) -> StatisticsResult<CostProfile> {
+    let path = data_dir.join(COST_PROFILE_FILE);
+    let mut persisted = read_profiles(&path)?;
+
+    profile.version = persisted.profiles.last().map_or(1, |latest| latest.version + 1);
+    persisted.profiles.push(profile.clone());
+    let excess = persisted.profiles.len().saturating_sub(PROFILE_HISTORY + 1);
+    persisted.profiles.drain(..excess);
+
+    let data = serde_json::to_vec_pretty(&persisted).map_err(|e| StatisticsError::StorageError(e.to_string()))?;
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, data).map_err(|e| StatisticsError::StorageError(e.to_string()))?;
+    std::fs::rename(&tmp_path, &path).map_err(|e| StatisticsError::StorageError(e.to_string()))?;
+
+    Ok(profile)
+}
+
+/// Run the micro-benchmarks against `storage_dir`
+///
+/// The read benchmarks use a scratch file in `storage_dir` that is removed
+/// afterwards. On Linux the file is dropped from the page cache before each read
+/// pass so the timings reflect the device rather than memory.
+pub fn calibrate(storage_dir: &Path, config: &CalibrationConfig) -> StatisticsResult<CalibrationMeasurements> {
+    if config.page_size == 0 || config.file_pages == 0 || config.random_reads == 0 || config.comparisons == 0 || config.hash_probes == 0 {
+        return Err(StatisticsError::InvalidConfiguration("calibration sizes must be positive".to_string()));
+    }
+    let io_err = |e: std::io::Error| StatisticsError::StorageError(e.to_string());
+
+    let mut scratch = tempfile::NamedTempFile::new_in(storage_dir).map_err(io_err)?;
+    let mut rng = rand::thread_rng();
+    let mut page = vec![0u8; config.page_size];
+    for _ in 0..config.file_pages {
+        rng.fill(page.as_mut_slice());
+        scratch.write_all(&page).map_err(io_err)?;
+    }
+    scratch.as_file().sync_all().map_err(io_err)?;
+
+    let mut file = File::open(scratch.path()).map_err(io_err)?;
+    drop_page_cache(&file);
+    let started = Instant::now();
+    for _ in 0..config.file_pages {
+        file.read_exact(&mut page).map_err(io_err)?;
+        black_box(&page);
+    }
+    let sequential_page_read_ns = per_op_ns(started, config.file_pages);
+
+    drop_page_cache(&file);
+    let offsets: Vec<u64> = (0..config.random_reads).map(|_| rng.gen_range(0..config.file_pages) as u64 * config.page_size as u64).collect();
+    let started = Instant::now();
+    for offset in &offsets {
+        file.seek(SeekFrom::Start(*offset)).map_err(io_err)?;
+        file.read_exact(&mut page).map_err(io_err)?;
+        black_box(&page);
+    }
+    let random_page_read_ns = per_op_ns(started, config.random_reads);
+
+    // Field values as documents hold them, compared the way a filter evaluates a predicate
+    let values: Vec<Value> = (0..1024).map(|i| Value::String(format!("user-{:08}", i * 7919 % 100_000))).collect();
+    let target = Value::String("user-00050000".to_string());
+    let started = Instant::now();
+    let mut matches = 0usize;
+    for i in 0..config.comparisons {
+        if black_box(&values[i % values.len()]) == black_box(&target) {
+            matches += 1;
+        }
+    }
+    black_box(matches);
+    let comparison_ns = per_op_ns(started, config.comparisons);
+
+    let table: HashMap<String, u64> = (0..65_536u64).map(|i| (format!("key-{}", i), i)).collect();
+    let probes: Vec<String> = (0..4096).map(|_| format!("key-{}", rng.gen_range(0..131_072u64))).collect();
+    let started = Instant::now();
+    let mut found = 0u64;
+    for i in 0..config.hash_probes {
+        if let Some(value) = table.get(black_box(&probes[i % probes.len()])) {
+            found = found.wrapping_add(*value);
+        }
+    }
+    black_box(found);
+    let hash_probe_ns = per_op_ns(started, config.hash_probes);
+
+    Ok(CalibrationMeasurements {
+        page_size: config.page_size,
+        sequential_page_read_ns,
+        random_page_read_ns,
+        comparison_ns,
+        hash_probe_ns,
+    })
+}
+
+fn per_op_ns(started: Instant, operations: usize) -> f64 {
+    // Keep the ratios finite when a pass is faster than the clock resolution
+    (started.elapsed().as_nanos() as f64 / operations as f64).max(1e-3)
+}
+
+#[cfg(target_os = "linux")]
+fn drop_page_cache(file: &File) {
+    use std::os::unix::io::AsRawFd;
+    // Best effort: without it the reads are served from memory and both read costs come out similar
+    unsafe {
+        libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn drop_page_cache(_file: &File) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_config() -> CalibrationConfig {
+        CalibrationConfig {
+            page_size: 4096,
+            file_pages: 64,
+            random_reads: 64,
+            comparisons: 10_000,
+            hash_probes: 10_000,
+        }
+    }
+
+    #[test]
+    fn test_calibrate_measures_every_operation() {
+        let dir = tempfile::tempdir().unwrap();
+        let measurements = calibrate(dir.path(), &small_config()).unwrap();
+
+        assert_eq!(measurements.page_size, 4096);
+        assert!(measurements.sequential_page_read_ns > 0.0);
+        assert!(measurements.random_page_read_ns > 0.0);
+        assert!(measurements.comparison_ns > 0.0);
+        assert!(measurements.hash_probe_ns > 0.0);
+        // The scratch file is gone
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_profile_is_relative_to_sequential_reads() {
+        let profile = CostProfile::from_measurements(CalibrationMeasurements {
+            page_size: 8192,
+            sequential_page_read_ns: 2000.0,
+            random_page_read_ns: 8000.0,
+            comparison_ns: 20.0,
+            hash_probe_ns: 40.0,
+        });
+
+        assert_eq!(profile.sequential_page_cost, 1.0);
+        assert_eq!(profile.random_page_cost, 4.0);
+        assert_eq!(profile.cpu_cost_per_row, 0.01);
+        assert_eq!(profile.hash_probe_cost, 0.02);
+    }
+
+    #[test]
+    fn test_saving_versions_profiles() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(load_cost_profile(dir.path()).unwrap().is_none());
+
+        let measurements = calibrate(dir.path(), &small_config()).unwrap();
+        for expected in 1..=PROFILE_HISTORY as u64 + 3 {
+            let saved = save_cost_profile(dir.path(), CostProfile::from_measurements(measurements)).unwrap();
+            assert_eq!(saved.version, expected);
+        }
+
+        assert_eq!(load_cost_profile(dir.path()).unwrap().unwrap().version, PROFILE_HISTORY as u64 + 3);
+        let history = cost_profile_history(dir.path()).unwrap();
+        assert_eq!(history.len(), PROFILE_HISTORY + 1);
+        assert_eq!(history[0].version, 3);
+    }
+}
@@ -59,6 +59,8 @@ pub struct HyperLogLogEstimator {
 }
 
 impl HyperLogLogEstimator {
+    const SERIALIZATION_VERSION: u8 = 1;
+
     pub fn new(precision: u8) -> Result<Self, CardinalityError> {
         if !(4..=16).contains(&precision) {
             return Err(CardinalityError::InvalidPrecision(precision));
@@ -86,6 +88,11 @@ impl HyperLogLogEstimator {
 
     pub fn add<T: Hash>(&mut self, value: &T) {
         let hash = self.hash_value(value);
+        self.add_hash(hash);
+    }
+
+    /// Add a value that has already been hashed with the estimator's hasher
+    pub fn add_hash(&mut self, hash: u64) {
         let bucket_index = (hash >> (64 - self.precision)) as usize;
         let leading_zeros = ((hash << self.precision) | (1 << self.precision)).leading_zeros() + 1;
 
@@ -115,11 +122,25 @@ impl HyperLogLogEstimator {
         corrected_estimate.round() as u64
     }
 
+    /// Merge another sketch into this one, producing the sketch of the union.
+    ///
+    /// Sketches of different precisions are merged at the coarser of the two:
+    /// the finer sketch is folded down with [`HyperLogLogEstimator::downgrade`]
+    /// and, if `self` is the finer one, `self` is downgraded in place. Merging
+    /// never fails on a precision mismatch, but it can lose accuracy.
     pub fn merge(&mut self, other: &HyperLogLogEstimator) -> Result<(), CardinalityError> {
-        if self.precision != other.precision {
-            return Err(CardinalityError::InvalidPrecision(other.precision));
+        if other.precision < self.precision {
+            *self = self.downgrade(other.precision)?;
         }
 
+        let folded;
+        let other = if other.precision > self.precision {
+            folded = other.downgrade(self.precision)?;
+            &folded
+        } else {
+            other
+        };
+
         for (i, &other_value) in other.buckets.iter().enumerate() {
             self.buckets[i] = self.buckets[i].max(other_value);
         }
@@ -127,6 +148,84 @@ impl HyperLogLogEstimator {
         Ok(())
     }
 
+    /// Fold this sketch down to a lower precision.
+    ///
+    /// Each coarse register covers `2^(p - precision)` fine registers. The
+    /// index bits that are dropped become the leading bits of the remaining
+    /// hash, so the rank is recomputed from them; the result is identical to
+    /// having built the sketch at the lower precision from the start.
+    pub fn downgrade(&self, precision: u8) -> Result<Self, CardinalityError> {
+        if precision > self.precision {
+            return Err(CardinalityError::InvalidPrecision(precision));
+        }
+
+        let mut result = Self::new(precision)?;
+        let shift = (self.precision - precision) as u32;
+
+        for (index, &rank) in self.buckets.iter().enumerate() {
+            if rank == 0 {
+                continue;
+            }
+
+            let low_bits = index & ((1 << shift) - 1);
+            let new_rank = if low_bits != 0 {
+                // Leading zeros of the dropped bits within a `shift`-bit field
+                (shift - (usize::BITS - low_bits.leading_zeros()) + 1) as u8
+            } else {
+                shift as u8 + rank
+            };
+
+            let bucket = &mut result.buckets[index >> shift];
+            *bucket = (*bucket).max(new_rank);
+        }
+
+        Ok(result)
+    }
+
+    /// Serialize the sketch as `[version, precision, registers...]`.
+    ///
+    /// Registers are only comparable between builds that hash values the same
+    /// way, so persisted sketches should be reloaded by the same binary.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(2 + self.buckets.len());
+        bytes.push(Self::SERIALIZATION_VERSION);
+        bytes.push(self.precision);
+        bytes.extend_from_slice(&self.buckets);
+        bytes
+    }
+
+    /// Restore a sketch written by [`HyperLogLogEstimator::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CardinalityError> {
+        let (&version, rest) = bytes.split_first().ok_or_else(|| CardinalityError::SerializationError("empty sketch".to_string()))?;
+        if version != Self::SERIALIZATION_VERSION {
+            return Err(CardinalityError::SerializationError(format!("unsupported sketch version {}", version)));
+        }
+
+        let (&precision, registers) = rest.split_first().ok_or_else(|| CardinalityError::SerializationError("missing precision".to_string()))?;
+        let mut estimator = Self::new(precision)?;
+
+        if registers.len() != estimator.bucket_count {
+            return Err(CardinalityError::SerializationError(format!(
+                "expected {} registers for precision {}, found {}",
+                estimator.bucket_count,
+                precision,
+                registers.len()
+            )));
+        }
+
+        let max_rank = 64 - precision;
+        if let Some(&bad) = registers.iter().find(|&&r| r > max_rank) {
+            return Err(CardinalityError::SerializationError(format!("register value {} exceeds maximum {}", bad, max_rank)));
+        }
+
+        estimator.buckets.copy_from_slice(registers);
+        Ok(estimator)
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
     fn hash_value<T: Hash>(&self, value: &T) -> u64 {
         use std::collections::hash_map::DefaultHasher;
         let mut hasher = DefaultHasher::new();
@@ -198,7 +297,7 @@ impl CardinalityEstimator {
                         if let Some(ref mut hll) = self.hll_estimator {
                             // Add all existing values to HLL
                             for &existing_hash in set.iter() {
-                                hll.add(&existing_hash);
+                                hll.add_hash(existing_hash);
                             }
                             hll.add(value);
                         }
@@ -241,11 +340,46 @@ impl CardinalityEstimator {
 
         if let (Some(self_hll), Some(other_hll)) = (&mut self.hll_estimator, &other.hll_estimator) {
             self_hll.merge(other_hll)?;
+            let merged_precision = self_hll.precision();
+            match &mut self.method {
+                CardinalityMethod::HyperLogLog { precision } | CardinalityMethod::Adaptive { precision, .. } => *precision = merged_precision,
+                CardinalityMethod::Exact => {}
+            }
         }
 
         Ok(())
     }
 
+    /// Build an estimator around an existing sketch, e.g. one reloaded from disk
+    pub fn from_sketch(sketch: HyperLogLogEstimator) -> Self {
+        let timestamp = crate::storage_engine::generate_timestamp();
+        Self {
+            method: CardinalityMethod::HyperLogLog { precision: sketch.precision() },
+            exact_set: None,
+            hll_estimator: Some(sketch),
+            count: 0,
+            created_at: timestamp,
+            last_updated: timestamp,
+        }
+    }
+
+    /// Produce a HyperLogLog sketch of everything added so far.
+    ///
+    /// Estimators still counting exactly are folded into a fresh sketch of
+    /// `precision`; estimators already using HyperLogLog return their own sketch.
+    pub fn to_sketch(&self, precision: u8) -> Result<HyperLogLogEstimator, CardinalityError> {
+        match &self.exact_set {
+            Some(set) => {
+                let mut sketch = HyperLogLogEstimator::new(precision)?;
+                for &hash in set {
+                    sketch.add_hash(hash);
+                }
+                Ok(sketch)
+            }
+            None => self.hll_estimator.clone().ok_or(CardinalityError::EmptyDataset),
+        }
+    }
+
     pub fn reset(&mut self) {
         self.count = 0;
         self.last_updated = crate::storage_engine::generate_timestamp();
@@ -459,6 +593,120 @@ mod tests {
         assert!(merged_estimate >= 1400 && merged_estimate <= 1600);
     }
 
+    #[test]
+    fn test_merged_estimate_accuracy() {
+        // Overlapping shards, as produced by scanning partitions independently
+        let shards: Vec<Vec<u64>> = (0..8u64).map(|shard| (shard * 7_500..shard * 7_500 + 12_000).collect()).collect();
+        let exact: HashSet<u64> = shards.iter().flatten().copied().collect();
+
+        let mut merged = HyperLogLogEstimator::new(14).unwrap();
+        for shard in &shards {
+            let mut hll = HyperLogLogEstimator::new(14).unwrap();
+            for value in shard {
+                hll.add(value);
+            }
+            merged.merge(&hll).unwrap();
+        }
+
+        let error_rate = (merged.estimate() as f64 - exact.len() as f64).abs() / exact.len() as f64;
+        assert!(error_rate < 0.03, "error rate {} too high", error_rate);
+    }
+
+    #[test]
+    fn test_downgrade_matches_direct_build() {
+        let mut fine = HyperLogLogEstimator::new(14).unwrap();
+        let mut coarse = HyperLogLogEstimator::new(10).unwrap();
+        for i in 0..20_000 {
+            fine.add(&format!("value{}", i));
+            coarse.add(&format!("value{}", i));
+        }
+
+        let downgraded = fine.downgrade(10).unwrap();
+        assert_eq!(downgraded.precision(), 10);
+        assert_eq!(downgraded.buckets, coarse.buckets);
+
+        assert!(matches!(coarse.downgrade(14), Err(CardinalityError::InvalidPrecision(14))));
+    }
+
+    #[test]
+    fn test_merge_mixed_precision_uses_coarser() {
+        let mut fine = HyperLogLogEstimator::new(14).unwrap();
+        let mut coarse = HyperLogLogEstimator::new(11).unwrap();
+        for i in 0..30_000 {
+            fine.add(&i);
+        }
+        for i in 20_000..50_000 {
+            coarse.add(&i);
+        }
+
+        let mut left = fine.clone();
+        left.merge(&coarse).unwrap();
+        let mut right = coarse.clone();
+        right.merge(&fine).unwrap();
+
+        assert_eq!(left.precision(), 11);
+        assert_eq!(right.precision(), 11);
+        assert_eq!(left.buckets, right.buckets);
+
+        let error_rate = (left.estimate() as f64 - 50_000.0).abs() / 50_000.0;
+        assert!(error_rate < 0.08, "error rate {} too high", error_rate);
+    }
+
+    #[test]
+    fn test_sketch_serialization_round_trip() {
+        let mut hll = HyperLogLogEstimator::new(12).unwrap();
+        for i in 0..5_000 {
+            hll.add(&i);
+        }
+
+        let bytes = hll.to_bytes();
+        assert_eq!(bytes.len(), 2 + 4096);
+
+        let restored = HyperLogLogEstimator::from_bytes(&bytes).unwrap();
+        assert_eq!(restored.precision(), 12);
+        assert_eq!(restored.buckets, hll.buckets);
+        assert_eq!(restored.estimate(), hll.estimate());
+    }
+
+    #[test]
+    fn test_sketch_deserialization_rejects_corrupt_input() {
+        let bytes = HyperLogLogEstimator::new(8).unwrap().to_bytes();
+
+        assert!(matches!(HyperLogLogEstimator::from_bytes(&[]), Err(CardinalityError::SerializationError(_))));
+        assert!(matches!(HyperLogLogEstimator::from_bytes(&bytes[..100]), Err(CardinalityError::SerializationError(_))));
+
+        let mut bad_version = bytes.clone();
+        bad_version[0] = 99;
+        assert!(matches!(HyperLogLogEstimator::from_bytes(&bad_version), Err(CardinalityError::SerializationError(_))));
+
+        let mut bad_precision = bytes.clone();
+        bad_precision[1] = 20;
+        assert!(matches!(HyperLogLogEstimator::from_bytes(&bad_precision), Err(CardinalityError::InvalidPrecision(20))));
+
+        let mut bad_register = bytes;
+        bad_register[2] = 64;
+        assert!(matches!(HyperLogLogEstimator::from_bytes(&bad_register), Err(CardinalityError::SerializationError(_))));
+    }
+
+    #[test]
+    fn test_estimator_sketch_round_trip() {
+        let mut estimator = CardinalityEstimator::new(CardinalityMethod::Exact).unwrap();
+        for i in 0..1_000 {
+            estimator.add(&i);
+        }
+
+        let sketch = estimator.to_sketch(14).unwrap();
+        let mut restored = CardinalityEstimator::from_sketch(sketch);
+        assert_eq!(restored.get_method(), &CardinalityMethod::HyperLogLog { precision: 14 });
+
+        // Re-adding the same values must not inflate the estimate
+        for i in 0..1_000 {
+            restored.add(&i);
+        }
+        let error_rate = (restored.estimate() as f64 - 1_000.0).abs() / 1_000.0;
+        assert!(error_rate < 0.05, "error rate {} too high", error_rate);
+    }
+
     #[test]
     fn test_multi_column_tracker() {
         let mut tracker = MultiColumnCardinalityTracker::new(CardinalityMethod::Exact);
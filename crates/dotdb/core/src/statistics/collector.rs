@@ -15,11 +15,23 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use thiserror::Error;
-use tokio::sync::RwLock;
+use tokio::sync::{RwLock, mpsc};
+use tracing::warn;
 
-use super::{AccessPatternTracker, BucketStrategy, CardinalityEstimator, CardinalityMethod, Histogram};
+use super::refresh::{
+    AnalyzeSource, AnalyzedTable, AutoRefreshConfig, MutationCounters, MutationKind, MutationRecorder, RefreshOutcome, RefreshStatus, RefreshTrigger, TableFreshness, analyze_sample,
+};
+use super::{AccessPatternTracker, BucketStrategy, CardinalityEstimator, CardinalityMethod, Histogram, HistogramType, HyperLogLogEstimator};
+
+/// Precision used when persisting estimators that are still counting exactly
+const DEFAULT_SKETCH_PRECISION: u8 = 14;
 
 #[derive(Debug, Error)]
 pub enum StatisticsError {
@@ -47,26 +59,57 @@ pub enum UpdateStrategy {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StatisticsConfig {
     pub max_histogram_buckets: usize,
+    /// `EqualFrequency` builds equi-depth histograms from a sample, `EqualWidth` equal-width ones from every value
+    #[serde(default = "default_histogram_type")]
+    pub histogram_type: HistogramType,
     pub cardinality_method: CardinalityMethod,
     pub update_strategy: UpdateStrategy,
     pub access_pattern_history_size: usize,
     pub enable_temporal_patterns: bool,
     pub statistics_retention_days: u32,
+    /// File the collected statistics are persisted to and reloaded from
+    #[serde(default)]
+    pub persistence_path: Option<PathBuf>,
+    /// Background re-analyze of tables that changed a lot since their last analyze
+    #[serde(default)]
+    pub auto_refresh: AutoRefreshConfig,
 }
 
 impl Default for StatisticsConfig {
     fn default() -> Self {
         Self {
             max_histogram_buckets: 100,
+            histogram_type: default_histogram_type(),
             cardinality_method: CardinalityMethod::default(),
             update_strategy: UpdateStrategy::Periodic { interval_seconds: 3600 },
             access_pattern_history_size: 10000,
             enable_temporal_patterns: true,
             statistics_retention_days: 30,
+            persistence_path: None,
+            auto_refresh: AutoRefreshConfig::default(),
         }
     }
 }
 
+impl StatisticsConfig {
+    /// How collected histograms lay out their buckets
+    pub fn bucket_strategy(&self) -> BucketStrategy {
+        match self.histogram_type {
+            HistogramType::EqualWidth => BucketStrategy::FixedWidth {
+                bucket_count: self.max_histogram_buckets,
+            },
+            HistogramType::EqualFrequency | HistogramType::Custom => BucketStrategy::EquiDepth {
+                bucket_count: self.max_histogram_buckets,
+                sample_size: self.auto_refresh.sample_size,
+            },
+        }
+    }
+}
+
+fn default_histogram_type() -> HistogramType {
+    HistogramType::EqualFrequency
+}
+
 #[derive(Debug)]
 struct TableStatistics {
     histograms: HashMap<String, Histogram>,
@@ -74,6 +117,69 @@ struct TableStatistics {
     access_tracker: AccessPatternTracker,
     row_count: u64,
     last_updated: u64,
+    /// Distinct values per column estimated by the last sampled analyze
+    sampled_distinct: HashMap<String, u64>,
+    mutations: MutationCounters,
+    last_analyzed: Option<u64>,
+    last_refresh: Option<RefreshOutcome>,
+    refresh_pending: bool,
+    last_refresh_started: Option<Instant>,
+    /// Raised when the table is dropped so its in-flight refresh is discarded
+    cancelled: Arc<AtomicBool>,
+}
+
+impl TableStatistics {
+    fn new(access_pattern_history_size: usize) -> Self {
+        Self {
+            histograms: HashMap::new(),
+            cardinality_estimators: HashMap::new(),
+            access_tracker: AccessPatternTracker::new(access_pattern_history_size),
+            row_count: 0,
+            last_updated: crate::storage_engine::generate_timestamp(),
+            sampled_distinct: HashMap::new(),
+            mutations: MutationCounters::default(),
+            last_analyzed: None,
+            last_refresh: None,
+            refresh_pending: false,
+            last_refresh_started: None,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Swap in statistics rebuilt by a refresh, keeping columns the sample did not cover
+    fn apply(&mut self, analyzed: AnalyzedTable, seen: &MutationCounters) {
+        self.histograms.extend(analyzed.histograms);
+        self.cardinality_estimators.extend(analyzed.estimators);
+        self.sampled_distinct.extend(analyzed.distinct);
+        self.row_count = analyzed.row_count;
+        self.mutations.subtract(seen);
+        let now = crate::storage_engine::generate_timestamp();
+        self.last_analyzed = Some(now);
+        self.last_updated = now;
+    }
+}
+
+/// On-disk form of a table's statistics
+#[derive(Debug, Serialize, Deserialize)]
+struct PersistedTableStatistics {
+    histograms: HashMap<String, Histogram>,
+    /// Hex-encoded `HyperLogLogEstimator::to_bytes` output, keyed by column
+    sketches: HashMap<String, String>,
+    row_count: u64,
+    last_updated: u64,
+    #[serde(default)]
+    sampled_distinct: HashMap<String, u64>,
+    #[serde(default)]
+    mutations: MutationCounters,
+    #[serde(default)]
+    last_analyzed: Option<u64>,
+    #[serde(default)]
+    last_refresh: Option<RefreshOutcome>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PersistedStatistics {
+    tables: HashMap<String, PersistedTableStatistics>,
 }
 
 #[derive(Debug)]
@@ -81,6 +187,8 @@ pub struct StatisticsCollector {
     config: StatisticsConfig,
     table_stats: RwLock<HashMap<String, TableStatistics>>,
     created_at: u64,
+    /// Tables waiting for the auto-refresh worker, once it is started
+    refresh_queue: Mutex<Option<mpsc::UnboundedSender<String>>>,
 }
 
 impl StatisticsCollector {
@@ -89,30 +197,25 @@ impl StatisticsCollector {
             config,
             table_stats: RwLock::new(HashMap::new()),
             created_at: crate::storage_engine::generate_timestamp(),
+            refresh_queue: Mutex::new(None),
         }
     }
 
     pub async fn collect_table_statistics(&self, table_name: &str) -> StatisticsResult<()> {
         let mut stats = self.table_stats.write().await;
 
-        let table_stats = stats.entry(table_name.to_string()).or_insert_with(|| TableStatistics {
-            histograms: HashMap::new(),
-            cardinality_estimators: HashMap::new(),
-            access_tracker: AccessPatternTracker::new(self.config.access_pattern_history_size),
-            row_count: 0,
-            last_updated: crate::storage_engine::generate_timestamp(),
-        });
+        let table_stats = stats
+            .entry(table_name.to_string())
+            .or_insert_with(|| TableStatistics::new(self.config.access_pattern_history_size));
 
         table_stats.last_updated = crate::storage_engine::generate_timestamp();
         Ok(())
     }
 
     pub async fn update_histogram(&self, table: &str, column: &str, data: &[f64]) -> StatisticsResult<()> {
-        let strategy = BucketStrategy::FixedWidth {
-            bucket_count: self.config.max_histogram_buckets,
-        };
-
-        let histogram = Histogram::create_with_strategy(strategy, data).map_err(|e| StatisticsError::CollectionFailed(e.to_string()))?;
+        let histogram = Histogram::create_with_strategy(self.config.bucket_strategy(), data)
+            .map_err(|e| StatisticsError::CollectionFailed(e.to_string()))?
+            .with_drift_factor(self.config.auto_refresh.bucket_drift_factor);
 
         let mut stats = self.table_stats.write().await;
         let table_stats = stats.get_mut(table).ok_or_else(|| StatisticsError::TableNotFound(table.to_string()))?;
@@ -134,13 +237,418 @@ impl StatisticsCollector {
         let stats = self.table_stats.read().await;
         let table_stats = stats.get(table).ok_or_else(|| StatisticsError::TableNotFound(table.to_string()))?;
 
-        Ok(table_stats.cardinality_estimators.get(column).map(|est| est.estimate()).unwrap_or(0))
+        // A sampled analyze sees only part of the table, so its scaled-up estimate can exceed the sketch's
+        let sketched = table_stats.cardinality_estimators.get(column).map(|est| est.estimate()).unwrap_or(0);
+        Ok(sketched.max(table_stats.sampled_distinct.get(column).copied().unwrap_or(0)))
+    }
+
+    /// Create a collector and reload any statistics persisted at `config.persistence_path`
+    pub async fn open(config: StatisticsConfig) -> StatisticsResult<Self> {
+        let collector = Self::new(config);
+        collector.load().await?;
+        Ok(collector)
+    }
+
+    pub async fn record_values<T: Hash>(&self, table: &str, column: &str, values: &[T]) -> StatisticsResult<()> {
+        let mut stats = self.table_stats.write().await;
+        let table_stats = stats.get_mut(table).ok_or_else(|| StatisticsError::TableNotFound(table.to_string()))?;
+
+        let estimator = match table_stats.cardinality_estimators.entry(column.to_string()) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                let estimator = CardinalityEstimator::new(self.config.cardinality_method.clone()).map_err(|e| StatisticsError::InvalidConfiguration(e.to_string()))?;
+                entry.insert(estimator)
+            }
+        };
+
+        for value in values {
+            estimator.add(value);
+        }
+        table_stats.last_updated = crate::storage_engine::generate_timestamp();
+
+        Ok(())
+    }
+
+    /// Merge a sketch collected elsewhere (another partition or node) into a column's estimator
+    pub async fn merge_cardinality_sketch(&self, table: &str, column: &str, sketch: &HyperLogLogEstimator) -> StatisticsResult<()> {
+        let mut stats = self.table_stats.write().await;
+        let table_stats = stats.get_mut(table).ok_or_else(|| StatisticsError::TableNotFound(table.to_string()))?;
+
+        let merged = match table_stats.cardinality_estimators.remove(column) {
+            Some(existing) => {
+                let mut merged = existing.to_sketch(sketch.precision()).map_err(|e| StatisticsError::CollectionFailed(e.to_string()))?;
+                merged.merge(sketch).map_err(|e| StatisticsError::CollectionFailed(e.to_string()))?;
+                merged
+            }
+            None => sketch.clone(),
+        };
+
+        table_stats.cardinality_estimators.insert(column.to_string(), CardinalityEstimator::from_sketch(merged));
+        table_stats.last_updated = crate::storage_engine::generate_timestamp();
+
+        Ok(())
+    }
+
+    pub async fn get_cardinality_sketch(&self, table: &str, column: &str) -> StatisticsResult<Option<HyperLogLogEstimator>> {
+        let stats = self.table_stats.read().await;
+        let table_stats = stats.get(table).ok_or_else(|| StatisticsError::TableNotFound(table.to_string()))?;
+
+        table_stats
+            .cardinality_estimators
+            .get(column)
+            .map(|est| est.to_sketch(self.sketch_precision()))
+            .transpose()
+            .map_err(|e| StatisticsError::CollectionFailed(e.to_string()))
+    }
+
+    /// Write histograms, row counts and cardinality sketches to `config.persistence_path`.
+    ///
+    /// Estimators are stored as HyperLogLog sketches, so after a reload every
+    /// column continues in HyperLogLog mode even if it was counting exactly.
+    pub async fn persist(&self) -> StatisticsResult<()> {
+        let path = self
+            .config
+            .persistence_path
+            .as_ref()
+            .ok_or_else(|| StatisticsError::InvalidConfiguration("no persistence path configured".to_string()))?;
+
+        let snapshot = {
+            let stats = self.table_stats.read().await;
+            let mut tables = HashMap::with_capacity(stats.len());
+            for (name, table) in stats.iter() {
+                let mut sketches = HashMap::with_capacity(table.cardinality_estimators.len());
+                for (column, estimator) in &table.cardinality_estimators {
+                    let sketch = estimator.to_sketch(self.sketch_precision()).map_err(|e| StatisticsError::StorageError(e.to_string()))?;
+                    sketches.insert(column.clone(), hex::encode(sketch.to_bytes()));
+                }
+                tables.insert(
+                    name.clone(),
+                    PersistedTableStatistics {
+                        histograms: table.histograms.clone(),
+                        sketches,
+                        row_count: table.row_count,
+                        last_updated: table.last_updated,
+                        sampled_distinct: table.sampled_distinct.clone(),
+                        mutations: table.mutations,
+                        last_analyzed: table.last_analyzed,
+                        last_refresh: table.last_refresh.clone(),
+                    },
+                );
+            }
+            PersistedStatistics { tables }
+        };
+
+        let data = serde_json::to_vec(&snapshot).map_err(|e| StatisticsError::StorageError(e.to_string()))?;
+
+        // Write to a temporary file first so a crash never leaves a truncated snapshot
+        let tmp_path = path.with_extension("tmp");
+        tokio::fs::write(&tmp_path, data).await.map_err(|e| StatisticsError::StorageError(e.to_string()))?;
+        tokio::fs::rename(&tmp_path, path).await.map_err(|e| StatisticsError::StorageError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Replace in-memory statistics with the persisted snapshot, if one exists
+    pub async fn load(&self) -> StatisticsResult<()> {
+        let Some(path) = self.config.persistence_path.as_ref() else {
+            return Ok(());
+        };
+
+        let data = match tokio::fs::read(path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(StatisticsError::StorageError(e.to_string())),
+        };
+
+        let snapshot: PersistedStatistics = serde_json::from_slice(&data).map_err(|e| StatisticsError::StorageError(e.to_string()))?;
+
+        let mut restored = HashMap::with_capacity(snapshot.tables.len());
+        for (name, table) in snapshot.tables {
+            let mut table_stats = TableStatistics::new(self.config.access_pattern_history_size);
+            table_stats.histograms = table.histograms;
+            table_stats.row_count = table.row_count;
+            table_stats.last_updated = table.last_updated;
+            table_stats.sampled_distinct = table.sampled_distinct;
+            table_stats.mutations = table.mutations;
+            table_stats.last_analyzed = table.last_analyzed;
+            table_stats.last_refresh = table.last_refresh;
+
+            for (column, encoded) in table.sketches {
+                let bytes = hex::decode(&encoded).map_err(|e| StatisticsError::StorageError(format!("sketch for {}.{}: {}", name, column, e)))?;
+                let sketch = HyperLogLogEstimator::from_bytes(&bytes).map_err(|e| StatisticsError::StorageError(format!("sketch for {}.{}: {}", name, column, e)))?;
+                table_stats.cardinality_estimators.insert(column, CardinalityEstimator::from_sketch(sketch));
+            }
+
+            restored.insert(name, table_stats);
+        }
+
+        *self.table_stats.write().await = restored;
+        Ok(())
+    }
+
+    /// Count writes to `table`, queueing a refresh once they pass the auto-refresh threshold
+    pub async fn record_mutation(&self, table: &str, kind: MutationKind, count: u64) {
+        let mut stats = self.table_stats.write().await;
+        let table_stats = stats
+            .entry(table.to_string())
+            .or_insert_with(|| TableStatistics::new(self.config.access_pattern_history_size));
+
+        table_stats.mutations.record(kind, count);
+        match kind {
+            MutationKind::Insert => table_stats.row_count += count,
+            MutationKind::Delete => table_stats.row_count = table_stats.row_count.saturating_sub(count),
+            MutationKind::Update => {}
+        }
+
+        if table_stats.mutations.total() > self.config.auto_refresh.threshold(table_stats.row_count) {
+            self.queue_refresh(table, table_stats);
+        }
+    }
+
+    /// Apply a write to a column's histogram: `old` is the value removed, if any, and `new` the value added
+    ///
+    /// Queues a refresh once the histogram has drifted far enough to need rebuilding.
+    pub async fn record_value_change(&self, table: &str, column: &str, old: Option<f64>, new: Option<f64>) -> StatisticsResult<()> {
+        let mut stats = self.table_stats.write().await;
+        let table_stats = stats.get_mut(table).ok_or_else(|| StatisticsError::TableNotFound(table.to_string()))?;
+        let Some(histogram) = table_stats.histograms.get_mut(column) else {
+            return Ok(());
+        };
+
+        if let Some(old) = old {
+            histogram.record_delete(old);
+        }
+        if let Some(new) = new {
+            histogram.record_insert(new);
+        }
+        if histogram.needs_rebuild {
+            self.queue_refresh(table, table_stats);
+        }
+        Ok(())
+    }
+
+    /// Hand `table` to the auto-refresh worker, unless it is already queued
+    fn queue_refresh(&self, table: &str, table_stats: &mut TableStatistics) {
+        if !self.config.auto_refresh.enabled || table_stats.refresh_pending {
+            return;
+        }
+        if let Some(queue) = self.refresh_queue.lock().unwrap().as_ref()
+            && queue.send(table.to_string()).is_ok()
+        {
+            table_stats.refresh_pending = true;
+        }
+    }
+
+    /// Start re-analyzing tables in the background as their mutation counters pass the threshold
+    /// or their histograms drift
+    ///
+    /// Refreshes run one at a time and at most once per `min_interval_secs` for
+    /// each table. The worker exits once the collector is dropped.
+    pub fn start_auto_refresh(self: &Arc<Self>, source: Arc<dyn AnalyzeSource>) -> tokio::task::JoinHandle<()> {
+        let (sender, mut queue) = mpsc::unbounded_channel::<String>();
+        *self.refresh_queue.lock().unwrap() = Some(sender);
+        let collector = Arc::downgrade(self);
+
+        tokio::spawn(async move {
+            while let Some(table) = queue.recv().await {
+                let delay = match collector.upgrade() {
+                    Some(collector) => collector.refresh_delay(&table).await,
+                    None => break,
+                };
+                if !delay.is_zero() {
+                    tokio::time::sleep(delay).await;
+                }
+
+                let Some(collector) = collector.upgrade() else { break };
+                if let Err(e) = collector.refresh(&table, source.clone(), RefreshTrigger::Mutations).await {
+                    warn!("Statistics refresh of {} failed: {}", table, e);
+                }
+            }
+        })
+    }
+
+    /// A recorder for writes made outside async code, and the worker counting them
+    ///
+    /// Must be called within a tokio runtime. Await the worker after dropping the
+    /// recorders to be sure every reported write has been counted.
+    pub fn mutation_recorder(self: &Arc<Self>) -> (MutationRecorder, tokio::task::JoinHandle<()>) {
+        let (sender, mut queue) = mpsc::unbounded_channel::<(String, MutationKind, u64)>();
+        let collector = self.clone();
+
+        let worker = tokio::spawn(async move {
+            while let Some((table, kind, count)) = queue.recv().await {
+                collector.record_mutation(&table, kind, count).await;
+            }
+        });
+        (MutationRecorder { sender }, worker)
+    }
+
+    /// Re-analyze `table` now from a sample of its rows
+    pub async fn analyze_table(&self, table: &str, source: Arc<dyn AnalyzeSource>) -> StatisticsResult<RefreshOutcome> {
+        self.refresh(table, source, RefreshTrigger::Manual).await
+    }
+
+    /// Forget a dropped table's statistics, cancelling any refresh queued or running for it
+    pub async fn drop_table(&self, table: &str) -> bool {
+        let removed = self.table_stats.write().await.remove(table);
+        if let Some(table_stats) = &removed {
+            table_stats.cancelled.store(true, Ordering::Release);
+        }
+        removed.is_some()
+    }
+
+    pub async fn freshness(&self, table: &str) -> StatisticsResult<TableFreshness> {
+        let stats = self.table_stats.read().await;
+        let table_stats = stats.get(table).ok_or_else(|| StatisticsError::TableNotFound(table.to_string()))?;
+
+        Ok(TableFreshness {
+            table: table.to_string(),
+            row_count: table_stats.row_count,
+            mutations: table_stats.mutations,
+            last_analyzed: table_stats.last_analyzed,
+            refresh_pending: table_stats.refresh_pending,
+            last_refresh: table_stats.last_refresh.clone(),
+        })
+    }
+
+    /// How long `table` must wait before the rate limit lets it refresh again
+    async fn refresh_delay(&self, table: &str) -> Duration {
+        let min_interval = Duration::from_secs(self.config.auto_refresh.min_interval_secs);
+        let stats = self.table_stats.read().await;
+        stats
+            .get(table)
+            .and_then(|table_stats| table_stats.last_refresh_started)
+            .map_or(Duration::ZERO, |started| min_interval.saturating_sub(started.elapsed()))
+    }
+
+    async fn refresh(&self, table: &str, source: Arc<dyn AnalyzeSource>, trigger: RefreshTrigger) -> StatisticsResult<RefreshOutcome> {
+        let started_at = crate::storage_engine::generate_timestamp();
+        let timer = Instant::now();
+        let mut outcome = RefreshOutcome {
+            trigger,
+            status: RefreshStatus::Cancelled,
+            started_at,
+            duration_ms: 0,
+            row_count: 0,
+            sampled_rows: 0,
+            bucket_boundaries: BTreeMap::new(),
+        };
+
+        let (cancelled, seen) = {
+            let mut stats = self.table_stats.write().await;
+            // A queued refresh of a table dropped since must not bring its statistics back
+            if outcome.trigger == RefreshTrigger::Mutations && !stats.contains_key(table) {
+                return Ok(outcome);
+            }
+            let table_stats = stats
+                .entry(table.to_string())
+                .or_insert_with(|| TableStatistics::new(self.config.access_pattern_history_size));
+            table_stats.last_refresh_started = Some(timer);
+            (table_stats.cancelled.clone(), table_stats.mutations)
+        };
+
+        let sample_size = self.config.auto_refresh.sample_size;
+        let strategy = self.config.bucket_strategy();
+        let drift_factor = self.config.auto_refresh.bucket_drift_factor;
+        let method = self.config.cardinality_method.clone();
+        let task_table = table.to_string();
+        let task_cancelled = cancelled.clone();
+        let analyzed = tokio::task::spawn_blocking(move || {
+            let row_count = source.row_count(&task_table)?;
+            if task_cancelled.load(Ordering::Acquire) {
+                return Ok(None);
+            }
+            let rows = source.sample_rows(&task_table, sample_size)?;
+            analyze_sample(&rows, row_count, &strategy, drift_factor, &method, &task_cancelled)
+        })
+        .await
+        .map_err(|e| StatisticsError::CollectionFailed(e.to_string()))
+        .and_then(|result| result);
+        outcome.duration_ms = timer.elapsed().as_millis() as u64;
+
+        {
+            let mut stats = self.table_stats.write().await;
+            // The table was dropped, and possibly recreated, while the sample was taken
+            let Some(table_stats) = stats.get_mut(table).filter(|table_stats| Arc::ptr_eq(&table_stats.cancelled, &cancelled)) else {
+                return Ok(outcome);
+            };
+            table_stats.refresh_pending = false;
+
+            match analyzed {
+                Ok(Some(analyzed)) => {
+                    outcome.status = RefreshStatus::Completed;
+                    outcome.row_count = analyzed.row_count;
+                    outcome.sampled_rows = analyzed.sampled_rows;
+                    outcome.bucket_boundaries = analyzed
+                        .histograms
+                        .iter()
+                        .map(|(column, histogram)| (column.clone(), histogram.buckets.iter().map(|bucket| bucket.range.max).collect()))
+                        .collect();
+                    table_stats.apply(analyzed, &seen);
+                    table_stats.last_refresh = Some(outcome.clone());
+                }
+                Ok(None) => return Ok(outcome),
+                Err(e) => {
+                    outcome.status = RefreshStatus::Failed(e.to_string());
+                    table_stats.last_refresh = Some(outcome);
+                    return Err(e);
+                }
+            }
+        }
+
+        if self.config.persistence_path.is_some() {
+            self.persist().await?;
+        }
+        Ok(outcome)
+    }
+
+    fn sketch_precision(&self) -> u8 {
+        match self.config.cardinality_method {
+            CardinalityMethod::HyperLogLog { precision } | CardinalityMethod::Adaptive { precision, .. } => precision,
+            CardinalityMethod::Exact => DEFAULT_SKETCH_PRECISION,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json::{Value, json};
+    use std::sync::mpsc;
+
+    struct FixedSource(Vec<Value>);
+
+    impl AnalyzeSource for FixedSource {
+        fn row_count(&self, _table: &str) -> StatisticsResult<u64> {
+            Ok(self.0.len() as u64)
+        }
+
+        fn sample_rows(&self, _table: &str, limit: usize) -> StatisticsResult<Vec<Value>> {
+            Ok(self.0.iter().take(limit).cloned().collect())
+        }
+    }
+
+    /// Blocks sampling until the test releases it
+    struct GatedSource {
+        entered: Mutex<mpsc::Sender<()>>,
+        release: Mutex<mpsc::Receiver<()>>,
+    }
+
+    impl AnalyzeSource for GatedSource {
+        fn row_count(&self, _table: &str) -> StatisticsResult<u64> {
+            Ok(1)
+        }
+
+        fn sample_rows(&self, _table: &str, _limit: usize) -> StatisticsResult<Vec<Value>> {
+            self.entered.lock().unwrap().send(()).unwrap();
+            self.release.lock().unwrap().recv().unwrap();
+            Ok(vec![json!({"age": 30})])
+        }
+    }
+
+    fn ages(count: usize) -> Arc<dyn AnalyzeSource> {
+        Arc::new(FixedSource((0..count).map(|i| json!({"age": i, "name": format!("user{}", i)})).collect()))
+    }
 
     #[tokio::test]
     async fn test_statistics_collector() {
@@ -155,4 +663,186 @@ mod tests {
         let histogram = collector.get_histogram("test_table", "test_column").await.unwrap();
         assert!(histogram.is_some());
     }
+
+    #[tokio::test]
+    async fn test_sketches_reloaded_on_startup() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = StatisticsConfig {
+            persistence_path: Some(dir.path().join("statistics.json")),
+            ..StatisticsConfig::default()
+        };
+
+        let collector = StatisticsCollector::new(config.clone());
+        collector.collect_table_statistics("users").await.unwrap();
+        let emails: Vec<String> = (0..25_000).map(|i| format!("user{}@example.com", i)).collect();
+        collector.record_values("users", "email", &emails).await.unwrap();
+        collector.update_histogram("users", "age", &[18.0, 25.0, 33.0, 47.0]).await.unwrap();
+        let before = collector.get_cardinality_estimate("users", "email").await.unwrap();
+        collector.persist().await.unwrap();
+
+        let reloaded = StatisticsCollector::open(config).await.unwrap();
+        assert_eq!(reloaded.get_cardinality_estimate("users", "email").await.unwrap(), before);
+        assert!(reloaded.get_histogram("users", "age").await.unwrap().is_some());
+
+        // Values seen before the restart are not counted twice
+        reloaded.record_values("users", "email", &emails[..1_000]).await.unwrap();
+        assert_eq!(reloaded.get_cardinality_estimate("users", "email").await.unwrap(), before);
+    }
+
+    #[tokio::test]
+    async fn test_merge_cardinality_sketch() {
+        let collector = StatisticsCollector::new(StatisticsConfig::default());
+        collector.collect_table_statistics("orders").await.unwrap();
+        let local: Vec<u64> = (0..40_000).collect();
+        collector.record_values("orders", "id", &local).await.unwrap();
+
+        let mut remote = HyperLogLogEstimator::new(12).unwrap();
+        for id in 30_000u64..80_000 {
+            remote.add(&id);
+        }
+        collector.merge_cardinality_sketch("orders", "id", &remote).await.unwrap();
+
+        let sketch = collector.get_cardinality_sketch("orders", "id").await.unwrap().unwrap();
+        assert_eq!(sketch.precision(), 12);
+
+        let estimate = collector.get_cardinality_estimate("orders", "id").await.unwrap();
+        let error_rate = (estimate as f64 - 80_000.0).abs() / 80_000.0;
+        assert!(error_rate < 0.06, "error rate {} too high", error_rate);
+    }
+
+    #[tokio::test]
+    async fn test_load_without_snapshot_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = StatisticsConfig {
+            persistence_path: Some(dir.path().join("missing.json")),
+            ..StatisticsConfig::default()
+        };
+
+        let collector = StatisticsCollector::open(config).await.unwrap();
+        assert!(matches!(collector.get_cardinality_estimate("users", "email").await, Err(StatisticsError::TableNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_mutations_trigger_background_refresh() {
+        let config = StatisticsConfig {
+            auto_refresh: AutoRefreshConfig {
+                min_mutations: 10,
+                min_interval_secs: 0,
+                ..AutoRefreshConfig::default()
+            },
+            ..StatisticsConfig::default()
+        };
+        let collector = Arc::new(StatisticsCollector::new(config));
+        collector.start_auto_refresh(ages(50));
+
+        collector.record_mutation("users", MutationKind::Insert, 10).await;
+        assert!(!collector.freshness("users").await.unwrap().refresh_pending);
+        collector.record_mutation("users", MutationKind::Update, 1).await;
+
+        let mut freshness = collector.freshness("users").await.unwrap();
+        for _ in 0..200 {
+            if freshness.last_refresh.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            freshness = collector.freshness("users").await.unwrap();
+        }
+
+        let outcome = freshness.last_refresh.expect("refresh never ran");
+        assert_eq!(outcome.status, RefreshStatus::Completed);
+        assert_eq!(outcome.trigger, RefreshTrigger::Mutations);
+        assert_eq!((outcome.row_count, outcome.sampled_rows), (50, 50));
+        assert!(outcome.bucket_boundaries.contains_key("age"));
+        assert_eq!(freshness.row_count, 50);
+        assert_eq!(freshness.mutations.total(), 0);
+        assert!(freshness.last_analyzed.is_some());
+        assert!(collector.get_histogram("users", "age").await.unwrap().is_some());
+        assert_eq!(collector.get_cardinality_estimate("users", "name").await.unwrap(), 50);
+    }
+
+    #[tokio::test]
+    async fn test_histogram_drift_triggers_background_refresh() {
+        let config = StatisticsConfig {
+            max_histogram_buckets: 10,
+            auto_refresh: AutoRefreshConfig {
+                min_interval_secs: 0,
+                ..AutoRefreshConfig::default()
+            },
+            ..StatisticsConfig::default()
+        };
+        let collector = Arc::new(StatisticsCollector::new(config));
+        collector.start_auto_refresh(ages(50));
+        collector.collect_table_statistics("users").await.unwrap();
+        let ages: Vec<f64> = (0..100).map(f64::from).collect();
+        collector.update_histogram("users", "age", &ages).await.unwrap();
+
+        // Ten more values in the first bucket still sit within twice its built depth
+        for age in 0..10 {
+            collector.record_value_change("users", "age", None, Some(age as f64 + 0.5)).await.unwrap();
+        }
+        assert!(!collector.freshness("users").await.unwrap().refresh_pending);
+        collector.record_value_change("users", "age", Some(50.0), Some(1.5)).await.unwrap();
+
+        let mut freshness = collector.freshness("users").await.unwrap();
+        for _ in 0..200 {
+            if freshness.last_refresh.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            freshness = collector.freshness("users").await.unwrap();
+        }
+
+        assert_eq!(freshness.last_refresh.expect("refresh never ran").trigger, RefreshTrigger::Mutations);
+        let histogram = collector.get_histogram("users", "age").await.unwrap().unwrap();
+        assert!(!histogram.needs_rebuild);
+        assert_eq!(histogram.total_count, 50);
+    }
+
+    #[tokio::test]
+    async fn test_drop_cancels_running_refresh() {
+        let (entered_tx, entered_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel();
+        let source = Arc::new(GatedSource {
+            entered: Mutex::new(entered_tx),
+            release: Mutex::new(release_rx),
+        });
+
+        let collector = Arc::new(StatisticsCollector::new(StatisticsConfig::default()));
+        collector.collect_table_statistics("users").await.unwrap();
+        let refresh = tokio::spawn({
+            let collector = collector.clone();
+            async move { collector.analyze_table("users", source).await }
+        });
+
+        tokio::task::spawn_blocking(move || entered_rx.recv().unwrap()).await.unwrap();
+        assert!(collector.drop_table("users").await);
+        release_tx.send(()).unwrap();
+
+        let outcome = refresh.await.unwrap().unwrap();
+        assert_eq!(outcome.status, RefreshStatus::Cancelled);
+        assert!(matches!(collector.get_histogram("users", "age").await, Err(StatisticsError::TableNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_outcome_readable_from_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(crate::statistics::STATISTICS_FILE);
+        let config = StatisticsConfig {
+            persistence_path: Some(path.clone()),
+            ..StatisticsConfig::default()
+        };
+
+        let collector = StatisticsCollector::new(config);
+        collector.analyze_table("users", ages(20)).await.unwrap();
+        collector.record_mutation("users", MutationKind::Delete, 2).await;
+        collector.persist().await.unwrap();
+
+        let freshness = TableFreshness::read_from(&path, "users").unwrap().unwrap();
+        assert_eq!(freshness.row_count, 18);
+        assert_eq!(freshness.mutations.deletes, 2);
+        assert!((freshness.mutation_ratio() - 2.0 / 18.0).abs() < 1e-9);
+        let outcome = freshness.last_refresh.unwrap();
+        assert_eq!((outcome.trigger, outcome.status), (RefreshTrigger::Manual, RefreshStatus::Completed));
+        assert!(TableFreshness::read_from(&path, "orders").unwrap().is_none());
+    }
 }
@@ -14,10 +14,15 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use thiserror::Error;
 
+/// Factor a bucket may grow or shrink by, relative to the depth it was built with, before its histogram needs rebuilding
+pub const DEFAULT_DRIFT_FACTOR: f64 = 2.0;
+
 /// Errors that can occur during histogram operations
 #[derive(Debug, Error)]
 pub enum HistogramError {
@@ -51,6 +56,9 @@ pub enum BucketStrategy {
     FixedFrequency { bucket_count: usize },
     /// Custom boundaries
     CustomBoundaries { boundaries: Vec<f64> },
+    /// About `bucket_count` buckets holding the same number of values each, placed at the
+    /// quantiles of a reservoir sample of at most `sample_size` values
+    EquiDepth { bucket_count: usize, sample_size: usize },
 }
 
 /// Represents a value range for histogram buckets
@@ -93,6 +101,9 @@ pub struct Bucket {
     pub distinct_values: u64,
     pub most_common_value: Option<f64>,
     pub mcv_frequency: u64,
+    /// `count` when an equi-depth histogram was built, the baseline its drift is measured against
+    #[serde(default)]
+    pub built_count: u64,
 }
 
 impl Bucket {
@@ -103,6 +114,7 @@ impl Bucket {
             distinct_values: 0,
             most_common_value: None,
             mcv_frequency: 0,
+            built_count: 0,
         }
     }
 
@@ -126,6 +138,103 @@ impl Bucket {
     pub fn selectivity(&self, total_count: u64) -> f64 {
         if total_count == 0 { 0.0 } else { self.count as f64 / total_count as f64 }
     }
+
+    /// Whether the bucket's values are all equal to `range.min`, so it holds no spread to interpolate over
+    fn is_single_valued(&self) -> bool {
+        self.distinct_values == 1 || self.range.width() == 0.0
+    }
+
+    /// Fraction of the bucket's values inside `range`, and whether that fraction is exact rather than interpolated
+    fn overlap(&self, range: &ValueRange) -> (f64, bool) {
+        if self.is_single_valued() {
+            return (if range.contains(self.range.min) { 1.0 } else { 0.0 }, true);
+        }
+        let low = self.range.min.max(range.min);
+        let high = self.range.max.min(range.max);
+        if high < low {
+            return (0.0, true);
+        }
+        let fraction = ((high - low) / self.range.width()).clamp(0.0, 1.0);
+        (fraction, fraction == 0.0 || fraction == 1.0)
+    }
+}
+
+/// A uniform random sample of a stream of values whose length is not known up front
+///
+/// Keeps the first `capacity` values, then replaces a random one with the
+/// n-th value seen with probability `capacity / n` (Vitter's algorithm R).
+#[derive(Debug, Clone)]
+pub struct ReservoirSample {
+    capacity: usize,
+    values: Vec<f64>,
+    seen: u64,
+    rng: StdRng,
+}
+
+impl ReservoirSample {
+    pub fn new(capacity: usize) -> Self {
+        Self::with_rng(capacity, StdRng::from_entropy())
+    }
+
+    /// A sample that picks the same values for the same input, for reproducible tests
+    pub fn with_seed(capacity: usize, seed: u64) -> Self {
+        Self::with_rng(capacity, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(capacity: usize, rng: StdRng) -> Self {
+        Self {
+            capacity,
+            values: Vec::with_capacity(capacity.min(1 << 16)),
+            seen: 0,
+            rng,
+        }
+    }
+
+    pub fn add(&mut self, value: f64) {
+        self.seen += 1;
+        if self.values.len() < self.capacity {
+            self.values.push(value);
+        } else if self.capacity > 0 {
+            let slot = self.rng.gen_range(0..self.seen);
+            if slot < self.capacity as u64 {
+                self.values[slot as usize] = value;
+            }
+        }
+    }
+
+    /// Number of values offered to the sample, kept or not
+    pub fn seen(&self) -> u64 {
+        self.seen
+    }
+
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+}
+
+impl Extend<f64> for ReservoirSample {
+    fn extend<I: IntoIterator<Item = f64>>(&mut self, values: I) {
+        for value in values {
+            self.add(value);
+        }
+    }
+}
+
+/// Estimated fraction of a column's values matching a predicate, with how far it can be trusted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SelectivityEstimate {
+    pub selectivity: f64,
+    /// From 0 to 1; lower when the histogram was built from a small sample, when the estimate
+    /// interpolates within buckets and as writes accumulate since the histogram was built
+    pub confidence: f64,
+}
+
+impl SelectivityEstimate {
+    /// Blend the estimate with `fallback` in proportion to the confidence, for a cost model
+    /// that would rather lean on a default than trust a shaky histogram
+    pub fn hedged(&self, fallback: f64) -> f64 {
+        self.confidence * self.selectivity + (1.0 - self.confidence) * fallback
+    }
 }
 
 /// Histogram for analyzing data distribution
@@ -139,6 +248,21 @@ pub struct Histogram {
     pub max_value: Option<f64>,
     pub created_at: u64,
     pub last_updated: u64,
+    /// Values the buckets were built from, fewer than `total_count` when built from a sample
+    #[serde(default)]
+    pub sampled_count: u64,
+    /// Inserts and deletes applied to the buckets since they were built
+    #[serde(default)]
+    pub changes_since_build: u64,
+    /// Set once a bucket drifted past `drift_factor` of the depth it was built with
+    #[serde(default)]
+    pub needs_rebuild: bool,
+    #[serde(default = "default_drift_factor")]
+    pub drift_factor: f64,
+}
+
+fn default_drift_factor() -> f64 {
+    DEFAULT_DRIFT_FACTOR
 }
 
 impl Histogram {
@@ -152,14 +276,29 @@ impl Histogram {
             max_value: None,
             created_at: crate::storage_engine::generate_timestamp(),
             last_updated: crate::storage_engine::generate_timestamp(),
+            sampled_count: 0,
+            changes_since_build: 0,
+            needs_rebuild: false,
+            drift_factor: DEFAULT_DRIFT_FACTOR,
         }
     }
 
+    pub fn with_drift_factor(mut self, drift_factor: f64) -> Self {
+        self.drift_factor = drift_factor;
+        self
+    }
+
     pub fn create_with_strategy(strategy: BucketStrategy, data: &[f64]) -> Result<Self, HistogramError> {
         if data.is_empty() {
             return Err(HistogramError::EmptyHistogram);
         }
 
+        if let BucketStrategy::EquiDepth { bucket_count, sample_size } = strategy {
+            let mut sample = ReservoirSample::new(sample_size);
+            sample.extend(data.iter().copied());
+            return Self::from_reservoir(bucket_count, &sample);
+        }
+
         let mut sorted_data = data.to_vec();
         sorted_data.sort_by(|a, b| a.partial_cmp(b).unwrap());
 
@@ -170,23 +309,103 @@ impl Histogram {
             BucketStrategy::FixedWidth { bucket_count } => Self::create_equal_width_buckets(*bucket_count, min_val, max_val, &sorted_data)?,
             BucketStrategy::FixedFrequency { bucket_count } => Self::create_equal_frequency_buckets(*bucket_count, &sorted_data)?,
             BucketStrategy::CustomBoundaries { boundaries } => Self::create_custom_buckets(boundaries.clone(), &sorted_data)?,
+            BucketStrategy::EquiDepth { .. } => unreachable!("equi-depth histograms are built from a sample"),
         };
 
         let histogram_type = match strategy {
             BucketStrategy::FixedWidth { .. } => HistogramType::EqualWidth,
             BucketStrategy::FixedFrequency { .. } => HistogramType::EqualFrequency,
             BucketStrategy::CustomBoundaries { .. } => HistogramType::Custom,
+            BucketStrategy::EquiDepth { .. } => HistogramType::EqualFrequency,
         };
 
         let mut histogram = Self::new(histogram_type);
         histogram.buckets = buckets;
         histogram.total_count = data.len() as u64;
+        histogram.sampled_count = data.len() as u64;
         histogram.min_value = Some(min_val);
         histogram.max_value = Some(max_val);
 
         Ok(histogram)
     }
 
+    /// Equi-depth histogram of every value offered to `sample`
+    pub fn from_reservoir(bucket_count: usize, sample: &ReservoirSample) -> Result<Self, HistogramError> {
+        Self::equi_depth(bucket_count, sample.values(), sample.seen())
+    }
+
+    /// Equi-depth histogram of `population` values, of which `sample` is a uniform sample
+    ///
+    /// Buckets end at the sample's quantiles, but never split a run of equal
+    /// values: a value sampled at least a bucket's depth worth of times gets a
+    /// bucket of its own, and shorter runs go to whichever side keeps the
+    /// bucket nearer the target depth. Counts are scaled up to `population`.
+    pub fn equi_depth(bucket_count: usize, sample: &[f64], population: u64) -> Result<Self, HistogramError> {
+        if bucket_count == 0 {
+            return Err(HistogramError::InvalidBucket("Bucket count must be greater than 0".to_string()));
+        }
+        let mut sorted: Vec<f64> = sample.iter().copied().filter(|value| !value.is_nan()).collect();
+        if sorted.is_empty() {
+            return Err(HistogramError::EmptyHistogram);
+        }
+        sorted.sort_by(f64::total_cmp);
+        let depth = (sorted.len() as f64 / bucket_count as f64).max(1.0);
+
+        // Split the sorted sample into bucket boundaries, as indexes into `sorted`
+        let mut starts = vec![0];
+        let mut run_start = 0;
+        while run_start < sorted.len() {
+            let run_end = run_start + sorted[run_start..].partition_point(|value| *value <= sorted[run_start]);
+            let bucket_start = *starts.last().unwrap();
+            let filled = (run_start - bucket_start) as f64;
+            let run = (run_end - run_start) as f64;
+            if filled > 0.0 && (run >= depth || (filled + run > depth && depth - filled < filled + run - depth)) {
+                starts.push(run_start);
+            }
+            if run_end < sorted.len() && (run >= depth || (run_end - *starts.last().unwrap()) as f64 >= depth) {
+                starts.push(run_end);
+            }
+            run_start = run_end;
+        }
+
+        let scale = population as f64 / sorted.len() as f64;
+        let mut buckets = Vec::with_capacity(starts.len());
+        for (i, &start) in starts.iter().enumerate() {
+            let end = starts.get(i + 1).copied().unwrap_or(sorted.len());
+            let last = end == sorted.len();
+            let values = &sorted[start..end];
+            let mut bucket = Bucket::new(ValueRange {
+                min: values[0],
+                max: if last { values[values.len() - 1] } else { sorted[end] },
+                inclusive_min: true,
+                inclusive_max: last,
+            });
+
+            let mut offset = 0;
+            while offset < values.len() {
+                let run = values[offset..].partition_point(|value| *value <= values[offset]);
+                bucket.distinct_values += 1;
+                if run as u64 > bucket.mcv_frequency {
+                    bucket.most_common_value = Some(values[offset]);
+                    bucket.mcv_frequency = run as u64;
+                }
+                offset += run;
+            }
+            bucket.mcv_frequency = (bucket.mcv_frequency as f64 * scale).round() as u64;
+            bucket.count = (values.len() as f64 * scale).round() as u64;
+            bucket.built_count = bucket.count;
+            buckets.push(bucket);
+        }
+
+        let mut histogram = Self::new(HistogramType::EqualFrequency);
+        histogram.buckets = buckets;
+        histogram.total_count = population;
+        histogram.sampled_count = sorted.len() as u64;
+        histogram.min_value = Some(sorted[0]);
+        histogram.max_value = Some(sorted[sorted.len() - 1]);
+        Ok(histogram)
+    }
+
     fn create_equal_width_buckets(bucket_count: usize, min_val: f64, max_val: f64, data: &[f64]) -> Result<Vec<Bucket>, HistogramError> {
         if bucket_count == 0 {
             return Err(HistogramError::InvalidBucket("Bucket count must be greater than 0".to_string()));
@@ -314,25 +533,98 @@ impl Histogram {
     }
 
     pub fn estimate_range_selectivity(&self, min: f64, max: f64) -> f64 {
+        self.estimate_range(&ValueRange::new(min, max)).selectivity
+    }
+
+    /// Estimate the fraction of values in `range`, assuming values spread evenly within each bucket
+    pub fn estimate_range(&self, range: &ValueRange) -> SelectivityEstimate {
         if self.total_count == 0 {
-            return 0.0;
+            return SelectivityEstimate { selectivity: 0.0, confidence: 0.0 };
         }
 
-        let mut total_count = 0u64;
-
+        let (mut exact, mut interpolated) = (0.0, 0.0);
         for bucket in &self.buckets {
-            // Check if bucket overlaps with the range
-            if bucket.range.max > min && bucket.range.min < max {
-                // Calculate overlap ratio
-                let overlap_min = bucket.range.min.max(min);
-                let overlap_max = bucket.range.max.min(max);
-                let overlap_ratio = (overlap_max - overlap_min) / bucket.range.width();
-
-                total_count += (bucket.count as f64 * overlap_ratio) as u64;
+            let (fraction, is_exact) = bucket.overlap(range);
+            let rows = bucket.count as f64 * fraction;
+            if is_exact {
+                exact += rows;
+            } else {
+                interpolated += rows;
             }
         }
+        let selectivity = ((exact + interpolated) / self.total_count as f64).min(1.0);
 
-        total_count as f64 / self.total_count as f64
+        SelectivityEstimate {
+            selectivity,
+            confidence: self.sampling_confidence(selectivity) * interpolation_confidence(exact, interpolated) * self.freshness(),
+        }
+    }
+
+    /// How closely a sample of `sampled_count` values pins down a selectivity, from its relative standard error
+    fn sampling_confidence(&self, selectivity: f64) -> f64 {
+        if self.sampled_count == 0 || self.sampled_count >= self.total_count {
+            return 1.0;
+        }
+        let sampled = self.sampled_count as f64;
+        let p = selectivity.clamp(1.0 / sampled, 1.0);
+        1.0 / (1.0 + ((1.0 - p) / (sampled * p)).sqrt())
+    }
+
+    /// Share of the current values the buckets were built from
+    fn freshness(&self) -> f64 {
+        let confidence = self.total_count as f64 / (self.total_count + self.changes_since_build) as f64;
+        if self.needs_rebuild { confidence / 2.0 } else { confidence }
+    }
+
+    /// Count a value inserted since the histogram was built
+    ///
+    /// Values outside the histogram's range widen its first or last bucket.
+    pub fn record_insert(&mut self, value: f64) {
+        let Some(index) = self.bucket_index(value) else { return };
+        let bucket = &mut self.buckets[index];
+        if bucket.distinct_values == 1 && value != bucket.range.min {
+            bucket.distinct_values = 2;
+        }
+        if value < bucket.range.min {
+            bucket.range.min = value;
+        }
+        if value > bucket.range.max {
+            bucket.range.max = value;
+        }
+        bucket.count += 1;
+        self.total_count += 1;
+        self.min_value = Some(self.min_value.map_or(value, |min| min.min(value)));
+        self.max_value = Some(self.max_value.map_or(value, |max| max.max(value)));
+        self.record_change();
+    }
+
+    /// Count a value deleted since the histogram was built
+    pub fn record_delete(&mut self, value: f64) {
+        let Some(index) = self.bucket_index(value) else { return };
+        self.buckets[index].count = self.buckets[index].count.saturating_sub(1);
+        self.total_count = self.total_count.saturating_sub(1);
+        self.record_change();
+    }
+
+    /// Bucket whose range holds `value`, or the first or last bucket for values outside them all
+    fn bucket_index(&self, value: f64) -> Option<usize> {
+        if self.buckets.is_empty() {
+            return None;
+        }
+        Some(self.buckets.partition_point(|bucket| bucket.range.min <= value).saturating_sub(1))
+    }
+
+    fn record_change(&mut self) {
+        self.changes_since_build += 1;
+        self.update_timestamp();
+        if self.needs_rebuild {
+            return;
+        }
+        let factor = self.drift_factor;
+        self.needs_rebuild = self.buckets.iter().filter(|bucket| bucket.built_count > 0).any(|bucket| {
+            let (count, built) = (bucket.count as f64, bucket.built_count as f64);
+            count > built * factor || count < built / factor
+        });
     }
 
     pub fn get_bucket_for_value(&self, value: f64) -> Option<&Bucket> {
@@ -374,6 +666,12 @@ impl Histogram {
     }
 }
 
+/// Lower when more of an estimate comes from interpolating inside partially covered buckets
+fn interpolation_confidence(exact: f64, interpolated: f64) -> f64 {
+    let total = exact + interpolated;
+    if total == 0.0 { 1.0 } else { (exact + interpolated / 2.0) / total }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -490,4 +788,124 @@ mod tests {
         let result = Histogram::create_with_strategy(strategy, &data);
         assert!(matches!(result, Err(HistogramError::InvalidBucket(_))));
     }
+
+    /// Ranks 1..=keys drawn with probability proportional to `1 / rank^exponent`
+    fn zipfian(count: usize, keys: usize, exponent: f64, seed: u64) -> Vec<f64> {
+        let mut cumulative = Vec::with_capacity(keys);
+        let mut total = 0.0;
+        for rank in 1..=keys {
+            total += 1.0 / (rank as f64).powf(exponent);
+            cumulative.push(total);
+        }
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..count)
+            .map(|_| {
+                let draw = rng.gen_range(0.0..total);
+                (cumulative.partition_point(|&c| c < draw) + 1) as f64
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reservoir_sample_is_bounded_and_uniform() {
+        let mut sample = ReservoirSample::with_seed(1_000, 7);
+        sample.extend((0..100_000).map(f64::from));
+
+        assert_eq!(sample.seen(), 100_000);
+        assert_eq!(sample.values().len(), 1_000);
+        let late = sample.values().iter().filter(|&&value| value >= 50_000.0).count();
+        assert!((400..600).contains(&late), "{} of 1000 sampled from the second half", late);
+    }
+
+    #[test]
+    fn test_equi_depth_isolates_frequent_values() {
+        let mut data = vec![5.0; 50];
+        data.extend((0..50).map(|i| 100.0 + i as f64));
+        let histogram = Histogram::create_with_strategy(BucketStrategy::EquiDepth { bucket_count: 4, sample_size: 1_000 }, &data).unwrap();
+
+        assert_eq!(histogram.histogram_type, HistogramType::EqualFrequency);
+        assert_eq!(histogram.total_count, 100);
+        let hot = &histogram.buckets[0];
+        assert_eq!((hot.range.min, hot.count, hot.distinct_values), (5.0, 50, 1));
+        assert!(histogram.buckets[1..].iter().all(|bucket| (20..=30).contains(&bucket.count)));
+
+        let estimate = histogram.estimate_range(&ValueRange::new(0.0, 6.0));
+        assert_eq!(estimate.selectivity, 0.5);
+        assert_eq!(estimate.confidence, 1.0);
+    }
+
+    #[test]
+    fn test_equi_depth_beats_equi_width_on_zipfian_data() {
+        let data = zipfian(200_000, 1_000, 1.1, 42);
+        let mut sample = ReservoirSample::with_seed(10_000, 42);
+        sample.extend(data.iter().copied());
+        let equi_depth = Histogram::from_reservoir(20, &sample).unwrap();
+        let equi_width = Histogram::create_with_strategy(BucketStrategy::FixedWidth { bucket_count: 20 }, &data).unwrap();
+        assert!(equi_depth.sampled_count < equi_depth.total_count);
+
+        let ranges = [(1.0, 1.0), (1.0, 3.0), (2.0, 5.0), (4.0, 10.0), (10.0, 50.0), (50.0, 200.0), (200.0, 1_000.0), (1.0, 100.0)];
+        let (mut depth_error, mut width_error) = (0.0, 0.0);
+        for (min, max) in ranges {
+            let range = ValueRange {
+                min,
+                max,
+                inclusive_min: true,
+                inclusive_max: true,
+            };
+            let actual = data.iter().filter(|&&value| range.contains(value)).count() as f64 / data.len() as f64;
+            depth_error += (equi_depth.estimate_range(&range).selectivity - actual).abs();
+            width_error += (equi_width.estimate_range(&range).selectivity - actual).abs();
+        }
+        let (depth_error, width_error) = (depth_error / ranges.len() as f64, width_error / ranges.len() as f64);
+
+        assert!(depth_error < 0.02, "equi-depth mean error {}", depth_error);
+        assert!(depth_error * 5.0 < width_error, "equi-depth {} vs equi-width {}", depth_error, width_error);
+    }
+
+    #[test]
+    fn test_interpolated_estimates_are_less_confident() {
+        let data: Vec<f64> = (0..1_000).map(f64::from).collect();
+        let histogram = Histogram::create_with_strategy(BucketStrategy::EquiDepth { bucket_count: 10, sample_size: 1_000 }, &data).unwrap();
+
+        let whole_buckets = histogram.estimate_range(&ValueRange::new(0.0, 500.0));
+        let partial_bucket = histogram.estimate_range(&ValueRange::new(0.0, 450.0));
+        assert!((whole_buckets.selectivity - 0.5).abs() < 1e-9);
+        assert!((partial_bucket.selectivity - 0.45).abs() < 1e-9);
+        assert!(partial_bucket.confidence < whole_buckets.confidence);
+        assert!((partial_bucket.hedged(0.33) - 0.45).abs() < (0.33f64 - 0.45).abs());
+    }
+
+    #[test]
+    fn test_drifted_bucket_marks_rebuild() {
+        let data: Vec<f64> = (0..100).map(f64::from).collect();
+        let mut histogram = Histogram::create_with_strategy(BucketStrategy::EquiDepth { bucket_count: 10, sample_size: 1_000 }, &data)
+            .unwrap()
+            .with_drift_factor(3.0);
+        let fresh = histogram.estimate_range(&ValueRange::new(0.0, 50.0)).confidence;
+
+        // Inserts spread over every bucket keep the depths even
+        for value in 0..100 {
+            histogram.record_insert(value as f64 + 0.5);
+        }
+        assert!(!histogram.needs_rebuild);
+        assert_eq!(histogram.total_count, 200);
+        assert!(histogram.estimate_range(&ValueRange::new(0.0, 50.0)).confidence < fresh);
+
+        for _ in 0..10 {
+            histogram.record_insert(1_000.0);
+        }
+        assert!(!histogram.needs_rebuild);
+        histogram.record_insert(1_000.0);
+        assert!(histogram.needs_rebuild);
+        assert_eq!(histogram.max_value, Some(1_000.0));
+        assert_eq!(histogram.buckets.last().unwrap().range.max, 1_000.0);
+
+        let mut shrinking = Histogram::create_with_strategy(BucketStrategy::EquiDepth { bucket_count: 10, sample_size: 1_000 }, &data).unwrap();
+        for value in 0..5 {
+            shrinking.record_delete(value as f64);
+        }
+        assert!(!shrinking.needs_rebuild);
+        shrinking.record_delete(5.0);
+        assert!(shrinking.needs_rebuild);
+    }
 }
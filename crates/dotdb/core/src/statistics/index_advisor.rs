@@ -0,0 +1,443 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Index recommendations from observed access patterns
+//!
+//! The advisor keeps a sliding window of field accesses (equality lookups, range scans
+//! and sorts) and of writes per collection. For each field it estimates how much work an
+//! index would have saved over the window against how much work keeping it up to date
+//! would have cost, and recommends the fields where the saving wins. Recommendations are
+//! derived from the window on every call, so one disappears once the accesses that
+//! motivated it age out.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::{StatisticsError, StatisticsResult};
+
+/// How a query used a field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FieldAccessKind {
+    /// `field = value`
+    Equality,
+    /// `field` compared against a bound
+    Range,
+    /// Results ordered by `field`
+    Sort,
+}
+
+/// One query's use of a field
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldAccess {
+    pub collection: String,
+    pub field: String,
+    pub kind: FieldAccessKind,
+    /// Documents the query had to read without an index on the field
+    pub rows_scanned: u64,
+    /// Documents the query returned
+    pub rows_returned: u64,
+}
+
+/// Kind of index a recommendation calls for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecommendedIndexKind {
+    /// Only equality lookups were seen
+    Hash,
+    /// Range scans or sorts need ordered keys
+    BTree,
+}
+
+/// A field worth indexing, with the estimates behind it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexRecommendation {
+    pub collection: String,
+    pub field: String,
+    pub index_kind: RecommendedIndexKind,
+    pub equality_lookups: u64,
+    pub range_scans: u64,
+    pub sorts: u64,
+    /// Full collection scans an index would have replaced
+    pub scans_avoided: u64,
+    /// Documents read and then discarded that an index would have skipped
+    pub rows_filtered: u64,
+    /// Estimated documents read and sorting work saved over the window
+    pub estimated_benefit: f64,
+    /// Estimated index updates caused by writes over the window
+    pub estimated_maintenance_cost: f64,
+}
+
+impl IndexRecommendation {
+    /// Net saving, used for ranking
+    pub fn net_benefit(&self) -> f64 {
+        self.estimated_benefit - self.estimated_maintenance_cost
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexAdvisorConfig {
+    /// How far back accesses and writes count
+    pub window: Duration,
+    /// Accesses to a field within the window before it is considered
+    pub min_accesses: u64,
+    /// Upper bound on retained events; the oldest are dropped first
+    pub max_events: usize,
+    /// File the observed window is persisted to and reloaded from
+    #[serde(default)]
+    pub persistence_path: Option<PathBuf>,
+}
+
+impl Default for IndexAdvisorConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(24 * 3600),
+            min_accesses: 10,
+            max_events: 100_000,
+            persistence_path: None,
+        }
+    }
+}
+
+/// On-disk form of the advisor's window
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PersistedAdvisor {
+    accesses: VecDeque<(u64, FieldAccess)>,
+    writes: VecDeque<(u64, String)>,
+    existing_indexes: HashSet<(String, String)>,
+}
+
+/// Per-field totals over the window
+#[derive(Default)]
+struct FieldTotals {
+    equality_lookups: u64,
+    range_scans: u64,
+    sorts: u64,
+    scans_avoided: u64,
+    rows_filtered: u64,
+    benefit: f64,
+    max_rows: u64,
+}
+
+/// Turns observed field accesses into ranked index recommendations
+#[derive(Debug)]
+pub struct IndexAdvisor {
+    config: IndexAdvisorConfig,
+    /// `(timestamp, access)`, oldest first
+    accesses: VecDeque<(u64, FieldAccess)>,
+    /// `(timestamp, collection)`, oldest first
+    writes: VecDeque<(u64, String)>,
+    /// `(collection, field)` pairs that are already indexed
+    existing_indexes: HashSet<(String, String)>,
+}
+
+impl IndexAdvisor {
+    pub fn new(config: IndexAdvisorConfig) -> Self {
+        Self {
+            config,
+            accesses: VecDeque::new(),
+            writes: VecDeque::new(),
+            existing_indexes: HashSet::new(),
+        }
+    }
+
+    /// Create an advisor and reload the window persisted at `config.persistence_path`
+    pub fn open(config: IndexAdvisorConfig) -> StatisticsResult<Self> {
+        let mut advisor = Self::new(config);
+        advisor.load()?;
+        Ok(advisor)
+    }
+
+    pub fn config(&self) -> &IndexAdvisorConfig {
+        &self.config
+    }
+
+    pub fn record_access(&mut self, access: FieldAccess) {
+        self.record_access_at(access, crate::storage_engine::generate_timestamp());
+    }
+
+    /// Record an access observed at `timestamp` (nanoseconds since the Unix epoch)
+    pub fn record_access_at(&mut self, access: FieldAccess, timestamp: u64) {
+        self.accesses.push_back((timestamp, access));
+        while self.accesses.len() > self.config.max_events {
+            self.accesses.pop_front();
+        }
+    }
+
+    /// Record a document write, which every index on the collection has to absorb
+    pub fn record_write(&mut self, collection: &str) {
+        self.record_write_at(collection, crate::storage_engine::generate_timestamp());
+    }
+
+    pub fn record_write_at(&mut self, collection: &str, timestamp: u64) {
+        self.writes.push_back((timestamp, collection.to_string()));
+        while self.writes.len() > self.config.max_events {
+            self.writes.pop_front();
+        }
+    }
+
+    /// Note that `field` is indexed so it is no longer recommended
+    pub fn register_index(&mut self, collection: &str, field: &str) {
+        self.existing_indexes.insert((collection.to_string(), field.to_string()));
+    }
+
+    pub fn unregister_index(&mut self, collection: &str, field: &str) {
+        self.existing_indexes.remove(&(collection.to_string(), field.to_string()));
+    }
+
+    /// Ranked recommendations from the window ending now
+    pub fn recommendations(&mut self) -> Vec<IndexRecommendation> {
+        self.recommendations_at(crate::storage_engine::generate_timestamp())
+    }
+
+    /// Ranked recommendations from the window ending at `now`, best first
+    pub fn recommendations_at(&mut self, now: u64) -> Vec<IndexRecommendation> {
+        self.expire(now);
+
+        let mut totals: HashMap<(&str, &str), FieldTotals> = HashMap::new();
+        for (_, access) in &self.accesses {
+            if self.existing_indexes.contains(&(access.collection.clone(), access.field.clone())) {
+                continue;
+            }
+            let field = totals.entry((access.collection.as_str(), access.field.as_str())).or_default();
+            match access.kind {
+                FieldAccessKind::Equality => field.equality_lookups += 1,
+                FieldAccessKind::Range => field.range_scans += 1,
+                FieldAccessKind::Sort => field.sorts += 1,
+            }
+            field.benefit += access_benefit(access);
+            field.max_rows = field.max_rows.max(access.rows_scanned);
+            if access.kind != FieldAccessKind::Sort {
+                field.scans_avoided += 1;
+                field.rows_filtered += access.rows_scanned.saturating_sub(access.rows_returned);
+            }
+        }
+
+        let mut writes_per_collection: HashMap<&str, u64> = HashMap::new();
+        for (_, collection) in &self.writes {
+            *writes_per_collection.entry(collection.as_str()).or_default() += 1;
+        }
+
+        let mut recommendations: Vec<IndexRecommendation> = totals
+            .into_iter()
+            .filter_map(|((collection, field), totals)| {
+                let accesses = totals.equality_lookups + totals.range_scans + totals.sorts;
+                if accesses < self.config.min_accesses {
+                    return None;
+                }
+
+                // Each write updates the index: a descent plus the entry itself
+                let writes = writes_per_collection.get(collection).copied().unwrap_or(0);
+                let maintenance_cost = writes as f64 * (log2(totals.max_rows) + 1.0);
+                if totals.benefit <= maintenance_cost {
+                    return None;
+                }
+
+                Some(IndexRecommendation {
+                    collection: collection.to_string(),
+                    field: field.to_string(),
+                    index_kind: if totals.range_scans + totals.sorts > 0 {
+                        RecommendedIndexKind::BTree
+                    } else {
+                        RecommendedIndexKind::Hash
+                    },
+                    equality_lookups: totals.equality_lookups,
+                    range_scans: totals.range_scans,
+                    sorts: totals.sorts,
+                    scans_avoided: totals.scans_avoided,
+                    rows_filtered: totals.rows_filtered,
+                    estimated_benefit: totals.benefit,
+                    estimated_maintenance_cost: maintenance_cost,
+                })
+            })
+            .collect();
+
+        recommendations.sort_by(|a, b| {
+            b.net_benefit()
+                .total_cmp(&a.net_benefit())
+                .then_with(|| a.collection.cmp(&b.collection))
+                .then_with(|| a.field.cmp(&b.field))
+        });
+        recommendations
+    }
+
+    /// Drop events that fell out of the window
+    fn expire(&mut self, now: u64) {
+        let cutoff = now.saturating_sub(self.config.window.as_nanos() as u64);
+        while self.accesses.front().is_some_and(|(ts, _)| *ts < cutoff) {
+            self.accesses.pop_front();
+        }
+        while self.writes.front().is_some_and(|(ts, _)| *ts < cutoff) {
+            self.writes.pop_front();
+        }
+    }
+
+    /// Write the window and known indexes to `config.persistence_path`
+    pub fn persist(&self) -> StatisticsResult<()> {
+        let path = self
+            .config
+            .persistence_path
+            .as_ref()
+            .ok_or_else(|| StatisticsError::InvalidConfiguration("no persistence path configured".to_string()))?;
+
+        let snapshot = PersistedAdvisor {
+            accesses: self.accesses.clone(),
+            writes: self.writes.clone(),
+            existing_indexes: self.existing_indexes.clone(),
+        };
+        let data = serde_json::to_vec(&snapshot).map_err(|e| StatisticsError::StorageError(e.to_string()))?;
+
+        // Write to a temporary file first so a crash never leaves a truncated snapshot
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, data).map_err(|e| StatisticsError::StorageError(e.to_string()))?;
+        std::fs::rename(&tmp_path, path).map_err(|e| StatisticsError::StorageError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Replace the in-memory window with the persisted one, if it exists
+    pub fn load(&mut self) -> StatisticsResult<()> {
+        let Some(path) = self.config.persistence_path.as_ref() else {
+            return Ok(());
+        };
+
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(StatisticsError::StorageError(e.to_string())),
+        };
+        let snapshot: PersistedAdvisor = serde_json::from_slice(&data).map_err(|e| StatisticsError::StorageError(e.to_string()))?;
+
+        self.accesses = snapshot.accesses;
+        self.writes = snapshot.writes;
+        self.existing_indexes = snapshot.existing_indexes;
+        Ok(())
+    }
+}
+
+fn log2(rows: u64) -> f64 {
+    (rows.max(1) as f64).log2()
+}
+
+/// Work an index on the field would have saved for one access, in documents read
+fn access_benefit(access: &FieldAccess) -> f64 {
+    let scanned = access.rows_scanned as f64;
+    match access.kind {
+        // A descent plus the matching entries instead of reading everything
+        FieldAccessKind::Equality | FieldAccessKind::Range => (scanned - (log2(access.rows_scanned) + access.rows_returned as f64)).max(0.0),
+        // Reading in index order replaces sorting the results
+        FieldAccessKind::Sort => access.rows_returned as f64 * log2(access.rows_returned),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: u64 = 1_000_000_000;
+
+    fn access(field: &str, kind: FieldAccessKind, rows_scanned: u64, rows_returned: u64) -> FieldAccess {
+        FieldAccess {
+            collection: "users".to_string(),
+            field: field.to_string(),
+            kind,
+            rows_scanned,
+            rows_returned,
+        }
+    }
+
+    fn advisor() -> IndexAdvisor {
+        IndexAdvisor::new(IndexAdvisorConfig {
+            window: Duration::from_secs(60),
+            min_accesses: 3,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_recommendations_ranked_by_net_benefit() {
+        let mut advisor = advisor();
+        let now = 1_000 * SECOND;
+        for _ in 0..10 {
+            advisor.record_access_at(access("email", FieldAccessKind::Equality, 10_000, 1), now);
+            advisor.record_access_at(access("age", FieldAccessKind::Range, 1_000, 100), now);
+        }
+        // Too few accesses to count
+        advisor.record_access_at(access("name", FieldAccessKind::Equality, 10_000, 1), now);
+
+        let recommendations = advisor.recommendations_at(now);
+        assert_eq!(recommendations.len(), 2);
+        assert_eq!(recommendations[0].field, "email");
+        assert_eq!(recommendations[0].index_kind, RecommendedIndexKind::Hash);
+        assert_eq!(recommendations[0].scans_avoided, 10);
+        assert_eq!(recommendations[0].rows_filtered, 10 * 9_999);
+        assert_eq!(recommendations[1].field, "age");
+        assert_eq!(recommendations[1].index_kind, RecommendedIndexKind::BTree);
+    }
+
+    #[test]
+    fn test_write_heavy_collections_not_recommended() {
+        let mut advisor = advisor();
+        let now = 1_000 * SECOND;
+        for _ in 0..5 {
+            advisor.record_access_at(access("status", FieldAccessKind::Equality, 100, 50), now);
+        }
+        assert_eq!(advisor.recommendations_at(now).len(), 1);
+
+        for _ in 0..100 {
+            advisor.record_write_at("users", now);
+        }
+        let recommendations = advisor.recommendations_at(now);
+        assert!(recommendations.is_empty());
+    }
+
+    #[test]
+    fn test_existing_indexes_and_expiry() {
+        let mut advisor = advisor();
+        let start = 1_000 * SECOND;
+        for _ in 0..5 {
+            advisor.record_access_at(access("email", FieldAccessKind::Equality, 10_000, 1), start);
+            advisor.record_access_at(access("created_at", FieldAccessKind::Sort, 10_000, 500), start);
+        }
+
+        advisor.register_index("users", "email");
+        let recommendations = advisor.recommendations_at(start);
+        assert_eq!(recommendations.len(), 1);
+        assert_eq!(recommendations[0].field, "created_at");
+
+        // Once the accesses leave the window the recommendation goes with them
+        assert!(advisor.recommendations_at(start + 61 * SECOND).is_empty());
+    }
+
+    #[test]
+    fn test_persist_and_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = IndexAdvisorConfig {
+            min_accesses: 1,
+            persistence_path: Some(dir.path().join("advisor.json")),
+            ..Default::default()
+        };
+
+        let mut advisor = IndexAdvisor::new(config.clone());
+        advisor.record_access(access("email", FieldAccessKind::Equality, 1_000, 1));
+        advisor.register_index("users", "age");
+        advisor.persist().unwrap();
+
+        let mut reloaded = IndexAdvisor::open(config).unwrap();
+        assert_eq!(reloaded.recommendations().len(), 1);
+        assert!(reloaded.existing_indexes.contains(&("users".to_string(), "age".to_string())));
+    }
+}
@@ -27,11 +27,15 @@
 //! - Collect and maintain histograms for data distribution analysis
 //! - Support for various data types and ranges
 //! - Configurable bucket strategies
+//! - Equi-depth histograms built from a reservoir sample, kept current between
+//!   analyzes by incremental bucket counts
+//! - Range selectivity estimates that report their confidence
 //!
 //! ## Cardinality Estimation
 //! - Track unique value counts for columns and indexes
 //! - HyperLogLog-based approximate counting for large datasets
 //! - Exact counting for smaller datasets
+//! - Mergeable sketches that persist across restarts
 //!
 //! ## Common Value Tracking
 //! - Identify and track most frequently accessed values
@@ -43,6 +47,21 @@
 //! - Track hot and cold data regions
 //! - Temporal access pattern analysis
 //!
+//! ## Index Advice
+//! - Rank fields worth indexing from equality lookups, range scans and sorts
+//! - Weigh scans avoided against index maintenance caused by writes
+//!
+//! ## Automatic Refresh
+//! - Count inserts, updates and deletes per table since its last analyze
+//! - Re-analyze from a row sample in the background once a table has changed enough
+//!
+//! ## Space Amplification
+//! - Compare each collection's logical size with the space its dead values still take on disk
+//!
+//! ## Cost Calibration
+//! - Benchmark page reads, comparisons and hash probes on the storage device
+//! - Persist versioned cost profiles the query planner's cost model loads
+//!
 //! # Usage
 //!
 //! ```rust
@@ -81,12 +100,20 @@
 //! ```
 
 pub mod access_patterns;
+pub mod calibration;
 pub mod cardinality;
 pub mod collector;
 pub mod histogram;
+pub mod index_advisor;
+pub mod refresh;
+pub mod space;
 
 // Re-export commonly used types
 pub use access_patterns::{AccessPattern, AccessPatternTracker, AccessStats, PatternType, TemporalAccessPattern};
+pub use calibration::{COST_PROFILE_FILE, CalibrationConfig, CalibrationMeasurements, CostProfile, calibrate, cost_profile_history, load_cost_profile, save_cost_profile};
 pub use cardinality::{CardinalityEstimator, CardinalityMethod, HyperLogLogEstimator};
 pub use collector::{StatisticsCollector, StatisticsConfig, StatisticsError, StatisticsResult, UpdateStrategy};
-pub use histogram::{Bucket, BucketStrategy, Histogram, HistogramType, ValueRange};
+pub use histogram::{Bucket, BucketStrategy, DEFAULT_DRIFT_FACTOR, Histogram, HistogramType, ReservoirSample, SelectivityEstimate, ValueRange};
+pub use index_advisor::{FieldAccess, FieldAccessKind, IndexAdvisor, IndexAdvisorConfig, IndexRecommendation, RecommendedIndexKind};
+pub use refresh::{AnalyzeSource, AutoRefreshConfig, MutationCounters, MutationKind, MutationRecorder, RefreshOutcome, RefreshStatus, RefreshTrigger, STATISTICS_FILE, TableFreshness};
+pub use space::CollectionSpaceUsage;
@@ -0,0 +1,365 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Automatic statistics refresh
+//!
+//! Writes bump per-table mutation counters. Once the mutations since the last
+//! analyze exceed a fraction of the table's size, the collector queues a
+//! re-analyze that samples rows from an [`AnalyzeSource`] and rebuilds the
+//! table's histograms and cardinality sketches from the sample. Equi-depth
+//! histograms whose buckets drift too far from their built depth queue one
+//! too, regardless of the mutation count.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::mpsc;
+
+use super::collector::{StatisticsError, StatisticsResult};
+use super::histogram::DEFAULT_DRIFT_FACTOR;
+use super::{BucketStrategy, CardinalityEstimator, CardinalityMethod, Histogram};
+
+/// File the statistics collector persists to inside a data directory
+pub const STATISTICS_FILE: &str = "statistics.json";
+
+/// When and how tables are re-analyzed after they change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoRefreshConfig {
+    pub enabled: bool,
+    /// Re-analyze once mutations since the last analyze exceed this fraction of the row count
+    pub mutation_fraction: f64,
+    /// Floor on the mutation threshold so small tables are not re-analyzed on every write
+    pub min_mutations: u64,
+    /// Shortest time between two refreshes of the same table
+    pub min_interval_secs: u64,
+    /// Rows sampled per refresh
+    pub sample_size: usize,
+    /// Queue a refresh once an equi-depth histogram bucket grows or shrinks by this factor
+    pub bucket_drift_factor: f64,
+}
+
+impl Default for AutoRefreshConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            mutation_fraction: 0.1,
+            min_mutations: 100,
+            min_interval_secs: 60,
+            sample_size: 10_000,
+            bucket_drift_factor: DEFAULT_DRIFT_FACTOR,
+        }
+    }
+}
+
+impl AutoRefreshConfig {
+    /// Mutations a table of `row_count` rows absorbs before it is considered stale
+    pub fn threshold(&self, row_count: u64) -> u64 {
+        ((row_count as f64 * self.mutation_fraction).ceil() as u64).max(self.min_mutations)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MutationKind {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// Writes to a table since it was last analyzed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MutationCounters {
+    pub inserts: u64,
+    pub updates: u64,
+    pub deletes: u64,
+}
+
+impl MutationCounters {
+    pub fn total(&self) -> u64 {
+        self.inserts + self.updates + self.deletes
+    }
+
+    pub(super) fn record(&mut self, kind: MutationKind, count: u64) {
+        match kind {
+            MutationKind::Insert => self.inserts += count,
+            MutationKind::Update => self.updates += count,
+            MutationKind::Delete => self.deletes += count,
+        }
+    }
+
+    /// Forget the mutations an analyze has accounted for, keeping any made while it ran
+    pub(super) fn subtract(&mut self, seen: &MutationCounters) {
+        self.inserts = self.inserts.saturating_sub(seen.inserts);
+        self.updates = self.updates.saturating_sub(seen.updates);
+        self.deletes = self.deletes.saturating_sub(seen.deletes);
+    }
+}
+
+/// Rows a refresh samples from
+///
+/// Called from a blocking thread, so implementations may do synchronous IO.
+pub trait AnalyzeSource: Send + Sync {
+    /// Number of rows currently in `table`
+    fn row_count(&self, table: &str) -> StatisticsResult<u64>;
+    /// Up to `limit` rows of `table` chosen uniformly at random
+    fn sample_rows(&self, table: &str, limit: usize) -> StatisticsResult<Vec<Value>>;
+}
+
+/// Reports writes to a collector from code that cannot await it
+///
+/// Created by [`StatisticsCollector::mutation_recorder`](super::StatisticsCollector::mutation_recorder);
+/// the writes are counted in the order they were reported by a worker that finishes
+/// once every clone of the recorder is dropped.
+#[derive(Debug, Clone)]
+pub struct MutationRecorder {
+    pub(super) sender: mpsc::UnboundedSender<(String, MutationKind, u64)>,
+}
+
+impl MutationRecorder {
+    pub fn record(&self, table: &str, kind: MutationKind, count: u64) {
+        // The worker only stops once the recorders are gone, so a failed send has nowhere to go
+        let _ = self.sender.send((table.to_string(), kind, count));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RefreshTrigger {
+    /// Queued because the table's mutation counter crossed the threshold, or one of its histograms drifted
+    Mutations,
+    /// Requested through `StatisticsCollector::analyze_table`
+    Manual,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RefreshStatus {
+    Completed,
+    /// The table was dropped while the refresh ran
+    Cancelled,
+    Failed(String),
+}
+
+/// What the last refresh of a table did
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefreshOutcome {
+    pub trigger: RefreshTrigger,
+    pub status: RefreshStatus,
+    /// Nanoseconds since the Unix epoch
+    pub started_at: u64,
+    pub duration_ms: u64,
+    pub row_count: u64,
+    pub sampled_rows: u64,
+    /// Upper bound of each histogram bucket, by column
+    pub bucket_boundaries: BTreeMap<String, Vec<f64>>,
+}
+
+/// How current a table's statistics are
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableFreshness {
+    pub table: String,
+    pub row_count: u64,
+    pub mutations: MutationCounters,
+    /// Nanoseconds since the Unix epoch of the last completed analyze
+    pub last_analyzed: Option<u64>,
+    pub refresh_pending: bool,
+    pub last_refresh: Option<RefreshOutcome>,
+}
+
+impl TableFreshness {
+    /// Fraction of the table changed since the last analyze
+    pub fn mutation_ratio(&self) -> f64 {
+        if self.row_count == 0 {
+            return if self.mutations.total() == 0 { 0.0 } else { 1.0 };
+        }
+        self.mutations.total() as f64 / self.row_count as f64
+    }
+
+    /// Read one table's freshness from a persisted statistics snapshot
+    ///
+    /// Returns `None` when the snapshot does not exist or has no entry for `table`.
+    pub fn read_from(path: &Path, table: &str) -> StatisticsResult<Option<Self>> {
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(StatisticsError::StorageError(e.to_string())),
+        };
+        let snapshot: FreshnessSnapshot = serde_json::from_slice(&data).map_err(|e| StatisticsError::StorageError(e.to_string()))?;
+
+        Ok(snapshot.tables.get(table).map(|entry| Self {
+            table: table.to_string(),
+            row_count: entry.row_count,
+            mutations: entry.mutations,
+            last_analyzed: entry.last_analyzed,
+            refresh_pending: false,
+            last_refresh: entry.last_refresh.clone(),
+        }))
+    }
+}
+
+/// The freshness fields of the collector's persisted snapshot
+#[derive(Deserialize)]
+struct FreshnessSnapshot {
+    tables: HashMap<String, FreshnessEntry>,
+}
+
+#[derive(Deserialize)]
+struct FreshnessEntry {
+    row_count: u64,
+    #[serde(default)]
+    mutations: MutationCounters,
+    #[serde(default)]
+    last_analyzed: Option<u64>,
+    #[serde(default)]
+    last_refresh: Option<RefreshOutcome>,
+}
+
+/// Statistics rebuilt from one sample
+pub(super) struct AnalyzedTable {
+    pub histograms: HashMap<String, Histogram>,
+    pub estimators: HashMap<String, CardinalityEstimator>,
+    /// Distinct values per column, scaled up from the sample
+    pub distinct: HashMap<String, u64>,
+    pub row_count: u64,
+    pub sampled_rows: u64,
+}
+
+/// Build histograms for numeric top-level fields and distinct-value estimates for every field
+///
+/// Returns `None` if `cancelled` is raised part way through.
+pub(super) fn analyze_sample(
+    rows: &[Value],
+    row_count: u64,
+    strategy: &BucketStrategy,
+    drift_factor: f64,
+    method: &CardinalityMethod,
+    cancelled: &AtomicBool,
+) -> StatisticsResult<Option<AnalyzedTable>> {
+    let mut numeric: HashMap<String, Vec<f64>> = HashMap::new();
+    let mut frequencies: HashMap<String, HashMap<String, u64>> = HashMap::new();
+
+    for row in rows {
+        let Value::Object(fields) = row else { continue };
+        for (field, value) in fields {
+            if value.is_null() {
+                continue;
+            }
+            if let Some(number) = value.as_f64() {
+                numeric.entry(field.clone()).or_default().push(number);
+            }
+            // Values are counted by their JSON encoding so 1 and "1" stay distinct
+            *frequencies.entry(field.clone()).or_default().entry(value.to_string()).or_default() += 1;
+        }
+    }
+
+    let mut analyzed = AnalyzedTable {
+        histograms: HashMap::new(),
+        estimators: HashMap::new(),
+        distinct: HashMap::new(),
+        row_count,
+        sampled_rows: rows.len() as u64,
+    };
+
+    for (field, values) in numeric {
+        if cancelled.load(Ordering::Acquire) {
+            return Ok(None);
+        }
+        let histogram = match strategy {
+            // The rows are already a sample, so scale the buckets up to the field's share of the table
+            BucketStrategy::EquiDepth { bucket_count, .. } => {
+                let population = (row_count.max(analyzed.sampled_rows) as f64 * values.len() as f64 / analyzed.sampled_rows as f64).round() as u64;
+                Histogram::equi_depth(*bucket_count, &values, population)
+            }
+            _ => Histogram::create_with_strategy(strategy.clone(), &values),
+        }
+        .map_err(|e| StatisticsError::CollectionFailed(format!("histogram for {}: {}", field, e)))?;
+        analyzed.histograms.insert(field, histogram.with_drift_factor(drift_factor));
+    }
+
+    for (field, counts) in frequencies {
+        if cancelled.load(Ordering::Acquire) {
+            return Ok(None);
+        }
+        let mut estimator = CardinalityEstimator::new(method.clone()).map_err(|e| StatisticsError::InvalidConfiguration(e.to_string()))?;
+        for value in counts.keys() {
+            estimator.add(value);
+        }
+        analyzed.distinct.insert(field.clone(), scale_distinct(&counts, analyzed.sampled_rows, row_count));
+        analyzed.estimators.insert(field, estimator);
+    }
+
+    Ok(Some(analyzed))
+}
+
+/// Guaranteed-error estimate of a column's distinct values from a sample
+///
+/// Values seen once in the sample stand in for the unsampled part of the
+/// table, so they are scaled by `sqrt(rows / sampled)`; values seen more
+/// often are assumed to be fully represented already.
+fn scale_distinct(counts: &HashMap<String, u64>, sampled_rows: u64, row_count: u64) -> u64 {
+    if sampled_rows == 0 || sampled_rows >= row_count {
+        return counts.len() as u64;
+    }
+    let singletons = counts.values().filter(|&&count| count == 1).count() as f64;
+    let repeated = counts.len() as f64 - singletons;
+    let scale = (row_count as f64 / sampled_rows as f64).sqrt();
+    ((scale * singletons + repeated).round() as u64).min(row_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn strategy() -> BucketStrategy {
+        BucketStrategy::EquiDepth { bucket_count: 10, sample_size: 1_000 }
+    }
+
+    #[test]
+    fn test_threshold_has_floor() {
+        let config = AutoRefreshConfig::default();
+        assert_eq!(config.threshold(0), 100);
+        assert_eq!(config.threshold(50_000), 5_000);
+    }
+
+    #[test]
+    fn test_analyze_sample_scales_distinct_values() {
+        let rows: Vec<Value> = (0..1_000).map(|i| json!({"id": i, "status": if i % 2 == 0 { "open" } else { "closed" }, "note": null})).collect();
+        let analyzed = analyze_sample(&rows, 100_000, &strategy(), DEFAULT_DRIFT_FACTOR, &CardinalityMethod::Exact, &AtomicBool::new(false))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(analyzed.sampled_rows, 1_000);
+        // Histograms built from the sample describe the whole table
+        assert_eq!((analyzed.histograms["id"].total_count, analyzed.histograms["id"].sampled_count), (100_000, 1_000));
+        assert!(!analyzed.histograms.contains_key("status"));
+        assert!(!analyzed.distinct.contains_key("note"));
+        // Every sampled id was unique, so ids are scaled by sqrt(100); repeated statuses are not
+        assert_eq!(analyzed.distinct["id"], 10_000);
+        assert_eq!(analyzed.distinct["status"], 2);
+        assert_eq!(analyzed.estimators["status"].estimate(), 2);
+    }
+
+    #[test]
+    fn test_analyze_sample_stops_when_cancelled() {
+        let rows = vec![json!({"id": 1})];
+        assert!(
+            analyze_sample(&rows, 1, &strategy(), DEFAULT_DRIFT_FACTOR, &CardinalityMethod::Exact, &AtomicBool::new(true))
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,69 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Space amplification of collections
+//!
+//! A collection's logical size is what its live documents, history and indexes
+//! take up. Overwritten and deleted values stay on disk until compaction rewrites
+//! the segments holding them, so the on-disk size can be much larger for
+//! collections with a lot of churn.
+//!
+//! Compressed collections also report how large their live document bodies are
+//! once decompressed against what they take up as stored.
+
+use serde::{Deserialize, Serialize};
+
+/// Logical and on-disk size of one collection
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CollectionSpaceUsage {
+    pub collection: String,
+    pub documents: u64,
+    /// Bytes of the live documents' bodies once decompressed
+    pub document_bytes: u64,
+    /// Bytes the same bodies take up as stored, compressed or not
+    pub stored_document_bytes: u64,
+    /// Bytes of every live key the collection owns
+    pub logical_bytes: u64,
+    /// Logical bytes plus the dead values of the collection's keys not yet compacted away
+    pub on_disk_bytes: u64,
+    pub dead_entries: u64,
+    /// Deleted documents whose history still records them, because reads as of
+    /// a time before the deletion remain possible
+    pub history_tombstones: u64,
+}
+
+impl CollectionSpaceUsage {
+    /// On-disk size over logical size; 1.0 when nothing is wasted
+    pub fn space_amplification(&self) -> f64 {
+        if self.logical_bytes == 0 {
+            return if self.on_disk_bytes == 0 { 1.0 } else { f64::INFINITY };
+        }
+        self.on_disk_bytes as f64 / self.logical_bytes as f64
+    }
+
+    /// Decompressed document size over stored document size; 1.0 for uncompressed collections
+    pub fn compression_ratio(&self) -> f64 {
+        if self.stored_document_bytes == 0 {
+            return 1.0;
+        }
+        self.document_bytes as f64 / self.stored_document_bytes as f64
+    }
+
+    /// Bytes compaction could reclaim
+    pub fn dead_bytes(&self) -> u64 {
+        self.on_disk_bytes.saturating_sub(self.logical_bytes)
+    }
+}
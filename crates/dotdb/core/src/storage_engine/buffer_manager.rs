@@ -17,15 +17,20 @@
 // Buffer management module
 // This module provides in-memory caching of pages, coordinates I/O operations, and implements buffer replacement policies. It manages the buffer pool, page pinning, flushing, and background writing.
 
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{HashMap, HashSet};
 use std::ops::{Deref, DerefMut};
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, RwLock, Weak};
 use std::thread;
 use std::time::{Duration, Instant};
 
+pub use crate::storage_engine::eviction::{AccessHint, EvictionPolicy, ReplacementPolicy};
 use crate::storage_engine::file_format::{FileFormat, Page, PageId, PageType};
 use crate::storage_engine::lib::{Flushable, Initializable, StorageError, StorageResult, VersionId, generate_timestamp};
+use crate::storage_engine::writeback::{self, Backpressure, WritebackQueue, WriterThroughput};
+
+/// How long a commit at the hard limit waits before queueing the dirty pages again
+const HARD_LIMIT_RECHECK: Duration = Duration::from_millis(100);
 
 /// Buffer pool statistics
 #[derive(Debug)]
@@ -40,6 +45,8 @@ pub struct BufferStats {
     pub misses: AtomicU64,
     /// Number of evictions
     pub evictions: AtomicU64,
+    /// Number of dirty pages written back on eviction
+    pub dirty_writebacks: AtomicU64,
 }
 
 impl Default for BufferStats {
@@ -56,6 +63,7 @@ impl BufferStats {
             hits: AtomicU64::new(0),
             misses: AtomicU64::new(0),
             evictions: AtomicU64::new(0),
+            dirty_writebacks: AtomicU64::new(0),
         }
     }
 
@@ -79,6 +87,10 @@ impl BufferStats {
         self.evictions.fetch_add(1, Ordering::Relaxed);
     }
 
+    pub fn inc_dirty_writebacks(&self) {
+        self.dirty_writebacks.fetch_add(1, Ordering::Relaxed);
+    }
+
     pub fn get_hit_ratio(&self) -> f64 {
         let hits = self.hits.load(Ordering::Relaxed);
         let total = hits + self.misses.load(Ordering::Relaxed);
@@ -97,17 +109,31 @@ impl BufferStats {
     }
 }
 
-/// Replacement policy enum
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum ReplacementPolicy {
-    /// Least recently used
-    LRU,
-    /// Clock-sweep (approximated LRU)
-    Clock,
-    /// Most recently used
-    MRU,
-    /// First in, first out
-    FIFO,
+/// Point-in-time snapshot of buffer pool counters, used to compare eviction policies
+#[derive(Debug, Clone, PartialEq)]
+pub struct BufferPoolStats {
+    /// Name of the active eviction policy
+    pub policy: &'static str,
+    /// Number of page lookups served from the pool
+    pub hits: u64,
+    /// Number of page lookups that went to disk
+    pub misses: u64,
+    /// Number of pages evicted
+    pub evictions: u64,
+    /// Number of dirty pages written back on eviction
+    pub dirty_writebacks: u64,
+    /// Number of resident pages
+    pub resident_pages: usize,
+    /// Maximum number of resident pages
+    pub capacity: usize,
+}
+
+impl BufferPoolStats {
+    /// Fraction of lookups served from the pool
+    pub fn hit_ratio(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 { 0.0 } else { self.hits as f64 / total as f64 }
+    }
 }
 
 /// Represents a buffer that holds a cached page in memory
@@ -121,10 +147,10 @@ pub struct Buffer {
     is_dirty: bool,
     /// Whether the buffer is pinned (cannot be evicted)
     pin_count: usize,
-    /// Clock bit for Clock replacement algorithm
-    clock_bit: AtomicBool,
     /// Insertion timestamp for FIFO policy
     insertion_time: u64,
+    /// Bumped on every mutable access, so a writeback can tell if the page changed under it
+    modifications: u64,
 }
 
 impl Buffer {
@@ -135,8 +161,8 @@ impl Buffer {
             last_accessed: Instant::now(),
             is_dirty: false,
             pin_count: 0,
-            clock_bit: AtomicBool::new(false),
             insertion_time: generate_timestamp(),
+            modifications: 0,
         }
     }
 
@@ -154,12 +180,6 @@ impl Buffer {
     /// Mark the buffer as accessed
     fn mark_accessed(&mut self) {
         self.last_accessed = Instant::now();
-        self.clock_bit.store(true, Ordering::SeqCst);
-    }
-
-    /// Reset the clock bit (for Clock replacement policy)
-    fn reset_clock_bit(&self) -> bool {
-        self.clock_bit.swap(false, Ordering::SeqCst)
     }
 
     /// Check if the buffer can be evicted
@@ -201,22 +221,22 @@ pub struct BufferPool {
     file_format: Arc<Mutex<FileFormat>>,
     /// Cached pages by ID
     buffers: HashMap<PageId, Buffer>,
-    /// LRU queue for eviction
-    lru_queue: VecDeque<PageId>,
     /// Maximum number of buffers
     capacity: usize,
     /// Statistics
     stats: BufferStats,
     /// Set of pages that are currently being read/written
     pending_io: HashSet<PageId>,
-    /// Replacement policy to use
+    /// Replacement policy selector
     policy: ReplacementPolicy,
-    /// Clock hand position (for Clock replacement policy)
-    clock_hand: usize,
+    /// Eviction policy state
+    eviction: Box<dyn EvictionPolicy>,
     /// Maximum number of dirty pages before forced flush
     max_dirty_pages: usize,
-    /// Background writer thread running flag
-    bg_writer_running: AtomicBool,
+    /// Pages modified since they were last written
+    dirty: HashSet<PageId>,
+    /// Queue of the background writers, fed once the dirty pages reach the start watermark
+    writeback: Arc<WritebackQueue>,
 }
 
 impl BufferPool {
@@ -225,20 +245,38 @@ impl BufferPool {
         Self {
             file_format,
             buffers: HashMap::with_capacity(config.buffer_pool_size),
-            lru_queue: VecDeque::with_capacity(config.buffer_pool_size),
             capacity: config.buffer_pool_size,
             stats: BufferStats::new(),
             pending_io: HashSet::new(),
-            policy: ReplacementPolicy::LRU, // Default to LRU
-            clock_hand: 0,
+            policy: config.eviction_policy,
+            eviction: config.eviction_policy.build(config.buffer_pool_size),
             max_dirty_pages: config.max_dirty_pages,
-            bg_writer_running: AtomicBool::new(false),
+            dirty: HashSet::new(),
+            writeback: Arc::new(WritebackQueue::new(config.writer_threads, config.max_dirty_pages)),
         }
     }
 
     /// Set the replacement policy
+    ///
+    /// Resident pages are re-registered with the new policy without any access history.
     pub fn set_policy(&mut self, policy: ReplacementPolicy) {
+        self.set_eviction_policy(policy, policy.build(self.capacity));
+    }
+
+    /// Install a custom eviction policy implementation
+    pub fn set_eviction_policy(&mut self, policy: ReplacementPolicy, mut eviction: Box<dyn EvictionPolicy>) {
+        let mut resident: Vec<(PageId, u64)> = self.buffers.iter().map(|(&id, buffer)| (id, buffer.get_insertion_time())).collect();
+        resident.sort_by_key(|&(id, inserted)| (inserted, id.0));
+        for (page_id, _) in resident {
+            eviction.record_insert(page_id, AccessHint::Normal);
+        }
         self.policy = policy;
+        self.eviction = eviction;
+    }
+
+    /// Get the active replacement policy
+    pub fn policy(&self) -> ReplacementPolicy {
+        self.policy
     }
 
     /// Get the number of buffers in the pool
@@ -258,29 +296,17 @@ impl BufferPool {
 
     /// Get a page from the buffer pool, reading from disk if necessary
     pub fn get_page(&mut self, page_id: PageId) -> StorageResult<&Buffer> {
+        self.get_page_with_hint(page_id, AccessHint::Normal)
+    }
+
+    /// Get a page, telling the eviction policy how the page will be used
+    ///
+    /// Scans should pass `AccessHint::UseOnce` so the pages they touch do not push the
+    /// working set out of the pool.
+    pub fn get_page_with_hint(&mut self, page_id: PageId, hint: AccessHint) -> StorageResult<&Buffer> {
         // Check if the page is already in the buffer pool
         if self.buffers.contains_key(&page_id) {
-            // Update LRU position or clock bit based on policy
-            match self.policy {
-                ReplacementPolicy::LRU => {
-                    self.lru_queue.retain(|&id| id != page_id);
-                    self.lru_queue.push_back(page_id);
-                }
-                ReplacementPolicy::Clock => {
-                    // Just set the clock bit, no need to update queue
-                    if let Some(buffer) = self.buffers.get_mut(&page_id) {
-                        buffer.clock_bit.store(true, Ordering::SeqCst);
-                    }
-                }
-                ReplacementPolicy::MRU => {
-                    // For MRU, move to front of queue
-                    self.lru_queue.retain(|&id| id != page_id);
-                    self.lru_queue.push_front(page_id);
-                }
-                ReplacementPolicy::FIFO => {
-                    // In FIFO we don't change the queue position on access
-                }
-            }
+            self.eviction.record_access(page_id, hint);
 
             // Mark as accessed
             if let Some(buffer) = self.buffers.get_mut(&page_id) {
@@ -320,27 +346,82 @@ impl BufferPool {
         let buffer = Buffer::new(page);
 
         self.buffers.insert(page_id, buffer);
-
-        // Add to appropriate data structure based on policy
-        match self.policy {
-            ReplacementPolicy::LRU | ReplacementPolicy::Clock | ReplacementPolicy::FIFO => {
-                self.lru_queue.push_back(page_id);
-            }
-            ReplacementPolicy::MRU => {
-                self.lru_queue.push_front(page_id);
-            }
-        }
+        self.eviction.record_insert(page_id, hint);
 
         Ok(&self.buffers[&page_id])
     }
 
     /// Get a mutable reference to a page buffer
+    ///
+    /// The page counts as dirty from here on, until it is written back.
     pub fn get_page_mut(&mut self, page_id: PageId) -> StorageResult<&mut Buffer> {
         // Try to get the page first
         self.get_page(page_id)?;
+        self.track_dirty(page_id);
 
         // Get a mutable reference
-        Ok(self.buffers.get_mut(&page_id).unwrap())
+        let buffer = self.buffers.get_mut(&page_id).unwrap();
+        buffer.is_dirty = true;
+        buffer.modifications += 1;
+        Ok(buffer)
+    }
+
+    /// Record `page_id` as dirty and hand dirty pages to the writers past the start watermark
+    fn track_dirty(&mut self, page_id: PageId) {
+        if !self.dirty.insert(page_id) {
+            return;
+        }
+        self.writeback.set_dirty_pages(self.dirty.len());
+
+        let start = writeback::start_watermark(self.max_dirty_pages);
+        if self.dirty.len() == start {
+            self.schedule_writeback();
+        } else if self.dirty.len() > start {
+            self.writeback.enqueue([page_id]);
+        }
+    }
+
+    /// Record `page_id` as written back or gone from the pool
+    fn track_clean(&mut self, page_id: PageId) {
+        if self.dirty.remove(&page_id) {
+            self.writeback.set_dirty_pages(self.dirty.len());
+        }
+    }
+
+    /// Queue every dirty page to the background writers
+    pub fn schedule_writeback(&self) {
+        self.writeback.enqueue(self.dirty.iter().copied());
+    }
+
+    /// Number of dirty pages
+    pub fn dirty_page_count(&self) -> usize {
+        self.dirty.len()
+    }
+
+    /// Queue of the background writers
+    pub fn writeback_queue(&self) -> Arc<WritebackQueue> {
+        Arc::clone(&self.writeback)
+    }
+
+    /// Storage file the pool reads from and writes to
+    pub(crate) fn file_format(&self) -> Arc<Mutex<FileFormat>> {
+        Arc::clone(&self.file_format)
+    }
+
+    /// Copy a dirty page for a background writer, with the modification count it was copied at
+    pub(crate) fn begin_writeback(&self, page_id: PageId) -> Option<(Page, u64)> {
+        self.buffers.get(&page_id).filter(|buffer| buffer.is_dirty()).map(|buffer| (buffer.page.clone(), buffer.modifications))
+    }
+
+    /// Mark a page clean after a background writer wrote it, unless it changed since it was copied
+    pub(crate) fn finish_writeback(&mut self, page_id: PageId, modifications: u64) {
+        self.stats.inc_writes();
+        if let Some(buffer) = self.buffers.get_mut(&page_id)
+            && buffer.modifications == modifications
+        {
+            buffer.mark_clean();
+            self.track_clean(page_id);
+        }
     }
 
     /// Pin a page in memory
@@ -386,6 +467,8 @@ impl BufferPool {
                 // Mark the buffer as clean
                 if result.is_ok() {
                     buffer.mark_clean();
+                    drop(file_format);
+                    self.track_clean(page_id);
                 }
 
                 result
@@ -450,16 +533,7 @@ impl BufferPool {
 
         // Add the page to the buffer pool
         self.buffers.insert(page_id, Buffer::new(page));
-
-        // Add to appropriate data structure based on policy
-        match self.policy {
-            ReplacementPolicy::LRU | ReplacementPolicy::Clock | ReplacementPolicy::FIFO => {
-                self.lru_queue.push_back(page_id);
-            }
-            ReplacementPolicy::MRU => {
-                self.lru_queue.push_front(page_id);
-            }
-        }
+        self.eviction.record_insert(page_id, AccessHint::Normal);
 
         Ok(page_id)
     }
@@ -467,157 +541,36 @@ impl BufferPool {
     /// Evicts a page from the buffer pool based on the selected policy.
     ///
     /// Steps:
-    /// 1. Depending on the replacement policy, call the appropriate eviction method (LRU, Clock, MRU, FIFO).
-    /// 2. Each policy attempts to find a non-pinned page to evict, flushes if dirty, and removes it from the pool.
-    /// 3. If all pages are pinned, returns BufferPoolFull error.
+    /// 1. Ask the eviction policy for a victim among the unpinned pages.
+    /// 2. If the victim is dirty, write it back to disk first.
+    /// 3. Remove it from the pool and from the policy's bookkeeping.
+    /// 4. If all pages are pinned, returns BufferPoolFull error.
     pub fn evict_one(&mut self) -> StorageResult<()> {
-        match self.policy {
-            ReplacementPolicy::LRU => self.evict_lru(),
-            ReplacementPolicy::Clock => self.evict_clock(),
-            ReplacementPolicy::MRU => self.evict_mru(),
-            ReplacementPolicy::FIFO => self.evict_fifo(),
-        }
-    }
-
-    /// Evict a page using the LRU policy
-    fn evict_lru(&mut self) -> StorageResult<()> {
-        // Find the least recently used page that is not pinned
-        while let Some(page_id) = self.lru_queue.pop_front() {
-            if let Some(buffer) = self.buffers.get(&page_id) {
-                if buffer.can_evict() {
-                    // If the page is dirty, flush it to disk
-                    if buffer.is_dirty() {
-                        let mut page_copy = buffer.page.clone();
-                        let mut file_format = self.file_format.lock().map_err(|_| StorageError::Corruption("Failed to lock file format".to_string()))?;
-
-                        self.stats.inc_writes();
-                        file_format.write_page(&mut page_copy)?;
-                    }
-
-                    // Remove the page from the buffer pool
-                    self.buffers.remove(&page_id);
-                    self.stats.inc_evictions();
-                    return Ok(());
-                } else {
-                    // Page is pinned, put it back at the end of the queue
-                    self.lru_queue.push_back(page_id);
-                }
-            }
-        }
-
-        // If we get here, all pages are pinned
-        Err(StorageError::BufferPoolFull)
-    }
-
-    /// Evict a page using the Clock policy
-    fn evict_clock(&mut self) -> StorageResult<()> {
-        // If there are no pages, nothing to evict
-        if self.lru_queue.is_empty() {
-            return Ok(());
-        }
-
-        // Clock hand algorithm - scan through pages in a circular fashion
-        let queue_size = self.lru_queue.len();
-        let starting_position = self.clock_hand % queue_size;
-
-        for i in 0..queue_size {
-            let check_pos = (starting_position + i) % queue_size;
-            let page_id = self.lru_queue[check_pos];
-
-            if let Some(buffer) = self.buffers.get(&page_id)
-                && buffer.can_evict()
-            {
-                // Check if the clock bit is reset
-                if !buffer.reset_clock_bit() {
-                    // Clock bit is 0, we can evict this page
-                    if buffer.is_dirty() {
-                        // Flush to disk first
-                        let mut page_copy = buffer.page.clone();
-                        let mut file_format = self.file_format.lock().map_err(|_| StorageError::Corruption("Failed to lock file format".to_string()))?;
-
-                        self.stats.inc_writes();
-                        file_format.write_page(&mut page_copy)?;
-                    }
-
-                    // Remove the page from queue and buffers
-                    self.lru_queue.remove(check_pos);
-                    self.buffers.remove(&page_id);
-                    self.stats.inc_evictions();
-
-                    // Update clock hand
-                    self.clock_hand = (check_pos + 1) % queue_size;
-
-                    return Ok(());
-                }
-                // Otherwise, clock bit is 1, so reset it and continue
-            }
+        let buffers = &self.buffers;
+        let victim = self.eviction.pick_victim(&|page_id| buffers.get(&page_id).is_some_and(Buffer::can_evict));
 
-            // Move clock hand to next position
-            self.clock_hand = (check_pos + 1) % queue_size;
-        }
-
-        // If we've gone through all pages and couldn't evict any, try LRU as fallback
-        self.evict_lru()
-    }
-
-    /// Evict a page using the MRU policy
-    fn evict_mru(&mut self) -> StorageResult<()> {
-        // Find the most recently used page that is not pinned
-        while let Some(page_id) = self.lru_queue.pop_front() {
-            if let Some(buffer) = self.buffers.get(&page_id) {
-                if buffer.can_evict() {
-                    // If the page is dirty, flush it to disk
-                    if buffer.is_dirty() {
-                        let mut page_copy = buffer.page.clone();
-                        let mut file_format = self.file_format.lock().map_err(|_| StorageError::Corruption("Failed to lock file format".to_string()))?;
+        let Some(page_id) = victim else {
+            return Err(StorageError::BufferPoolFull);
+        };
 
-                        self.stats.inc_writes();
-                        file_format.write_page(&mut page_copy)?;
-                    }
+        if let Some(buffer) = self.buffers.get(&page_id)
+            && buffer.is_dirty()
+        {
+            let mut page_copy = buffer.page.clone();
+            let mut file_format = self.file_format.lock().map_err(|_| StorageError::Corruption("Failed to lock file format".to_string()))?;
 
-                    // Remove the page from the buffer pool
-                    self.buffers.remove(&page_id);
-                    self.stats.inc_evictions();
-                    return Ok(());
-                } else {
-                    // Page is pinned, put it back at the back of the queue
-                    self.lru_queue.push_back(page_id);
-                }
-            }
+            self.stats.inc_writes();
+            self.stats.inc_dirty_writebacks();
+            file_format.write_page(&mut page_copy)?;
         }
 
-        // If we get here, all pages are pinned
-        Err(StorageError::BufferPoolFull)
-    }
-
-    /// Evict a page using the FIFO policy
-    fn evict_fifo(&mut self) -> StorageResult<()> {
-        // In FIFO, we just evict the page that was added first, regardless of access
-        while let Some(page_id) = self.lru_queue.pop_front() {
-            if let Some(buffer) = self.buffers.get(&page_id) {
-                if buffer.can_evict() {
-                    // If the page is dirty, flush it to disk
-                    if buffer.is_dirty() {
-                        let mut page_copy = buffer.page.clone();
-                        let mut file_format = self.file_format.lock().map_err(|_| StorageError::Corruption("Failed to lock file format".to_string()))?;
-
-                        self.stats.inc_writes();
-                        file_format.write_page(&mut page_copy)?;
-                    }
-
-                    // Remove the page from the buffer pool
-                    self.buffers.remove(&page_id);
-                    self.stats.inc_evictions();
-                    return Ok(());
-                } else {
-                    // Page is pinned, put it back at the end of the queue
-                    self.lru_queue.push_back(page_id);
-                }
-            }
-        }
+        // Remove the page from the buffer pool
+        self.buffers.remove(&page_id);
+        self.track_clean(page_id);
+        self.eviction.record_remove(page_id);
+        self.stats.inc_evictions();
 
-        // If we get here, all pages are pinned
-        Err(StorageError::BufferPoolFull)
+        Ok(())
     }
 
     /// Clear the buffer pool
@@ -627,7 +580,9 @@ impl BufferPool {
 
         // Clear the buffer pool
         self.buffers.clear();
-        self.lru_queue.clear();
+        self.dirty.clear();
+        self.writeback.set_dirty_pages(0);
+        self.eviction.clear();
         self.pending_io.clear();
 
         Ok(())
@@ -638,6 +593,19 @@ impl BufferPool {
         (&self.stats, self.buffers.len(), self.capacity)
     }
 
+    /// Snapshot of the pool counters
+    pub fn pool_stats(&self) -> BufferPoolStats {
+        BufferPoolStats {
+            policy: self.eviction.name(),
+            hits: self.stats.hits.load(Ordering::Relaxed),
+            misses: self.stats.misses.load(Ordering::Relaxed),
+            evictions: self.stats.evictions.load(Ordering::Relaxed),
+            dirty_writebacks: self.stats.dirty_writebacks.load(Ordering::Relaxed),
+            resident_pages: self.buffers.len(),
+            capacity: self.capacity,
+        }
+    }
+
     /// Check if the buffer pool contains a specific page (for testing)
     pub fn contains_page(&self, page_id: PageId) -> bool {
         self.buffers.contains_key(&page_id)
@@ -647,6 +615,35 @@ impl BufferPool {
     pub fn capacity(&self) -> usize {
         self.capacity
     }
+
+    /// Raise the capacity of the buffer pool
+    ///
+    /// Shrinking would have to evict resident pages and is rejected.
+    pub fn grow(&mut self, capacity: usize) -> StorageResult<()> {
+        if capacity < self.capacity {
+            return Err(StorageError::InvalidConfig {
+                setting: "buffer_pool_size".to_string(),
+                reason: format!("the buffer pool can grow but not shrink while running ({} < {} pages)", capacity, self.capacity),
+            });
+        }
+        self.buffers.reserve(capacity - self.capacity);
+        self.capacity = capacity;
+        Ok(())
+    }
+
+    /// Maximum number of dirty pages before a forced flush
+    pub fn max_dirty_pages(&self) -> usize {
+        self.max_dirty_pages
+    }
+
+    /// Set the maximum number of dirty pages before a forced flush
+    pub fn set_max_dirty_pages(&mut self, max_dirty_pages: usize) {
+        self.max_dirty_pages = max_dirty_pages;
+        self.writeback.set_max_dirty_pages(max_dirty_pages);
+        if self.dirty.len() >= writeback::start_watermark(max_dirty_pages) {
+            self.schedule_writeback();
+        }
+    }
 }
 
 impl Flushable for BufferPool {
@@ -661,8 +658,14 @@ pub struct BufferManager {
     pool: Arc<RwLock<BufferPool>>,
     /// Background flusher thread handle
     _flusher_handle: Option<thread::JoinHandle<()>>,
-    /// Signal to stop the flusher thread
-    stop_flusher: Arc<Mutex<bool>>,
+    /// Interval of the running flusher thread, if any
+    flush_interval: Option<Duration>,
+    /// Signal to stop the flusher thread, notified so a sleeping flusher wakes up
+    stop_flusher: Arc<(Mutex<bool>, Condvar)>,
+    /// Dirty pages queued to the background writers
+    writeback: Arc<WritebackQueue>,
+    /// Background writer threads, empty once the manager is closed
+    writers: Vec<thread::JoinHandle<()>>,
     /// Stats for the buffer manager
     stats: Arc<BufferStats>,
 }
@@ -671,15 +674,30 @@ impl BufferManager {
     /// Create a new buffer manager
     pub fn new(file_format: Arc<Mutex<FileFormat>>, config: &crate::storage_engine::lib::StorageConfig) -> Self {
         let buffer_pool = BufferPool::new(file_format, config);
+        let writeback = buffer_pool.writeback_queue();
         let stats = Arc::new(BufferStats::new());
 
         let pool = Arc::new(RwLock::new(buffer_pool));
-        let stop_flusher = Arc::new(Mutex::new(false));
+        let stop_flusher = Arc::new((Mutex::new(false), Condvar::new()));
+
+        let writers = (0..writeback.writer_count())
+            .map(|writer| {
+                let pool = Arc::clone(&pool);
+                let queue = Arc::clone(&writeback);
+                thread::Builder::new()
+                    .name(format!("buffer-writer-{writer}"))
+                    .spawn(move || writeback::run_writer(writer, pool, queue))
+                    .expect("Failed to start background writer thread")
+            })
+            .collect();
 
         let mut manager = Self {
             pool,
             _flusher_handle: None,
+            flush_interval: None,
             stop_flusher,
+            writeback,
+            writers,
             stats,
         };
 
@@ -702,22 +720,22 @@ impl BufferManager {
     pub fn start_flusher(&mut self, interval: Duration) -> StorageResult<()> {
         let pool_clone = self.pool.clone();
         let stop_flusher = self.stop_flusher.clone();
+        *self.stop_flusher.0.lock().unwrap() = false;
 
         let handle = thread::Builder::new()
             .name("buffer-flusher".into())
             .spawn(move || {
+                let (stop, wake) = &*stop_flusher;
                 loop {
-                    // Check if we should stop
+                    // Sleep for the interval, waking early if asked to stop
                     {
-                        let stop = stop_flusher.lock().unwrap();
+                        let stop = stop.lock().unwrap();
+                        let (stop, _) = wake.wait_timeout_while(stop, interval, |stop| !*stop).unwrap();
                         if *stop {
                             break;
                         }
                     }
 
-                    // Sleep for the interval
-                    thread::sleep(interval);
-
                     // Flush dirty pages
                     if let Ok(mut pool) = pool_clone.write()
                         && let Err(e) = pool.flush_all()
@@ -729,6 +747,7 @@ impl BufferManager {
             .map_err(|e| StorageError::Io(std::io::Error::other(format!("Failed to spawn flusher thread: {e}"))))?;
 
         self._flusher_handle = Some(handle);
+        self.flush_interval = Some(interval);
 
         Ok(())
     }
@@ -736,10 +755,13 @@ impl BufferManager {
     /// Stop the background flusher thread
     pub fn stop_flusher(&mut self) -> StorageResult<()> {
         if let Some(handle) = self._flusher_handle.take() {
+            self.flush_interval = None;
+
             // Signal the thread to stop
             {
-                let mut stop = self.stop_flusher.lock().unwrap();
-                *stop = true;
+                let (stop, wake) = &*self.stop_flusher;
+                *stop.lock().unwrap() = true;
+                wake.notify_all();
             }
 
             // Wait for the thread to finish
@@ -751,6 +773,109 @@ impl BufferManager {
         Ok(())
     }
 
+    /// Interval of the background flusher, `None` when it is not running
+    pub fn flush_interval(&self) -> Option<Duration> {
+        self.flush_interval
+    }
+
+    /// Restart the background flusher with a new interval; a zero interval stops it
+    pub fn set_flush_interval(&mut self, interval: Duration) -> StorageResult<()> {
+        self.stop_flusher()?;
+        if interval.is_zero() {
+            return Ok(());
+        }
+        self.start_flusher(interval)
+    }
+
+    /// Raise the capacity of the buffer pool; shrinking is rejected
+    pub fn grow_pool(&self, capacity: usize) -> StorageResult<()> {
+        let mut pool = self.pool.write().map_err(|_| StorageError::Corruption("Failed to acquire write lock on buffer pool".to_string()))?;
+
+        pool.grow(capacity)
+    }
+
+    /// Set the maximum number of dirty pages before a forced flush
+    pub fn set_max_dirty_pages(&self, max_dirty_pages: usize) -> StorageResult<()> {
+        let mut pool = self.pool.write().map_err(|_| StorageError::Corruption("Failed to acquire write lock on buffer pool".to_string()))?;
+
+        pool.set_max_dirty_pages(max_dirty_pages);
+        Ok(())
+    }
+
+    /// Number of dirty pages in the buffer pool
+    pub fn dirty_page_count(&self) -> usize {
+        self.writeback.dirty_pages()
+    }
+
+    /// Throughput of each background writer thread
+    pub fn writer_stats(&self) -> Vec<WriterThroughput> {
+        self.writeback.writer_stats()
+    }
+
+    /// Slows a commit down according to how many pages are dirty; returns how long it waited.
+    ///
+    /// Steps:
+    /// 1. Below the start watermark, return at once.
+    /// 2. Between the start watermark and the hard limit, sleep for a delay proportional
+    ///    to how close the dirty pages are to the hard limit.
+    /// 3. At the hard limit, queue every dirty page to the writers and wait until they
+    ///    bring the count back under it, queueing again if a write failed.
+    ///
+    /// The commit never writes pages back itself.
+    pub fn throttle_commit(&self) -> StorageResult<Duration> {
+        let started = Instant::now();
+        match self.writeback.backpressure() {
+            Backpressure::None => {}
+            Backpressure::Delay(delay) => thread::sleep(delay),
+            Backpressure::Block => loop {
+                {
+                    let pool = self.pool.read().map_err(|_| StorageError::Corruption("Failed to acquire read lock on buffer pool".to_string()))?;
+                    pool.schedule_writeback();
+                }
+                if self.writeback.wait_below_hard_limit(HARD_LIMIT_RECHECK) {
+                    break;
+                }
+            },
+        }
+        Ok(started.elapsed())
+    }
+
+    /// Flushes every dirty page and stops the background threads.
+    ///
+    /// Steps:
+    /// 1. Stop the periodic flusher.
+    /// 2. Queue every dirty page, let the writers drain their queues and join them.
+    /// 3. Flush whatever is still dirty, such as pages whose background write failed.
+    /// 4. Sync the storage file.
+    ///
+    /// Pages modified after `close` are only written by explicit flushes. Closing twice
+    /// only repeats the final flush.
+    pub fn close(&mut self) -> StorageResult<()> {
+        self.stop_flusher()?;
+
+        if !self.writers.is_empty() {
+            {
+                let pool = self.pool.read().map_err(|_| StorageError::Corruption("Failed to acquire read lock on buffer pool".to_string()))?;
+                pool.schedule_writeback();
+            }
+            self.writeback.wait_idle();
+            self.writeback.shutdown();
+            for writer in self.writers.drain(..) {
+                if writer.join().is_err() {
+                    return Err(StorageError::Io(std::io::Error::other("Failed to join background writer thread")));
+                }
+            }
+        }
+
+        let mut pool = self.pool.write().map_err(|_| StorageError::Corruption("Failed to acquire write lock on buffer pool".to_string()))?;
+        pool.flush_all()?;
+        let file_format = pool.file_format();
+        drop(pool);
+
+        let mut file_format = file_format.lock().map_err(|_| StorageError::Corruption("Failed to lock file format".to_string()))?;
+        file_format.sync()
+    }
+
     /// Gets a page from the buffer pool, reading from disk if necessary.
     ///
     /// Steps:
@@ -768,18 +893,25 @@ impl BufferManager {
         Ok(Arc::new(buffer.page.clone()))
     }
 
+    /// Gets a page that is only needed once, such as during a collection scan
+    ///
+    /// The page is read with `AccessHint::UseOnce`, so a scan larger than the pool evicts
+    /// its own pages before the working set.
+    pub fn get_page_for_scan(&self, page_id: PageId) -> StorageResult<Arc<Page>> {
+        let mut pool = self.pool.write().map_err(|_| StorageError::Corruption("Failed to acquire write lock on buffer pool".to_string()))?;
+        let buffer = pool.get_page_with_hint(page_id, AccessHint::UseOnce)?;
+        Ok(Arc::new(buffer.page.clone()))
+    }
+
     /// Get a page for update (returns a PageGuard)
     pub fn get_page_for_update(&self, page_id: PageId) -> StorageResult<PageGuard> {
         let mut pool = self.pool.write().map_err(|_| StorageError::Corruption("Failed to acquire write lock on buffer pool".to_string()))?;
 
-        // Get the page from the buffer pool
-        let buffer = pool.get_page_mut(page_id)?;
-
-        // Pin the page to prevent eviction
-        buffer.pin();
+        // Pin the page to prevent eviction; it only becomes dirty once the guard updates it
+        pool.pin_page(page_id)?;
 
         // Create a PageGuard
-        let page = buffer.page.clone();
+        let page = pool.buffers[&page_id].page.clone();
 
         Ok(PageGuard {
             page_id,
@@ -821,9 +953,17 @@ impl BufferManager {
             hits: AtomicU64::new(stats.hits.load(Ordering::Relaxed)),
             misses: AtomicU64::new(stats.misses.load(Ordering::Relaxed)),
             evictions: AtomicU64::new(stats.evictions.load(Ordering::Relaxed)),
+            dirty_writebacks: AtomicU64::new(stats.dirty_writebacks.load(Ordering::Relaxed)),
         })
     }
 
+    /// Get a snapshot of the buffer pool counters
+    pub fn pool_stats(&self) -> StorageResult<BufferPoolStats> {
+        let pool = self.pool.read().map_err(|_| StorageError::Corruption("Failed to acquire read lock on buffer pool".to_string()))?;
+
+        Ok(pool.pool_stats())
+    }
+
     /// Get direct access to the buffer pool for testing
     #[cfg(test)]
     pub fn get_buffer_pool_for_testing(&self) -> StorageResult<BufferPoolGuard<'_>> {
@@ -839,6 +979,14 @@ impl Flushable for BufferManager {
     }
 }
 
+impl Drop for BufferManager {
+    fn drop(&mut self) {
+        if let Err(e) = self.close() {
+            eprintln!("Error closing buffer manager: {e:?}");
+        }
+    }
+}
+
 impl Initializable for BufferManager {
     fn init(&mut self) -> StorageResult<()> {
         // Nothing specific to initialize
@@ -932,6 +1080,7 @@ mod tests {
     use super::*;
     use crate::storage_engine::file_format::{FileFormat, Page, PageId, PageType};
     use crate::storage_engine::lib::{Flushable, Initializable, StorageError, StorageResult, VersionId, calculate_checksum, generate_timestamp};
+    use crate::storage_engine::writeback::MAX_COMMIT_DELAY;
 
     fn create_test_file_format() -> Arc<Mutex<FileFormat>> {
         let storage_dir = tempfile::tempdir().unwrap();
@@ -1119,4 +1268,195 @@ mod tests {
         assert_eq!(stats.hits.load(Ordering::Relaxed), 2);
         assert_eq!(stats.misses.load(Ordering::Relaxed), 1);
     }
+
+    #[test]
+    fn test_lru_k_scan_resistance() {
+        let file_format = create_test_file_format();
+        let mut config = crate::storage_engine::lib::StorageConfig::default();
+        config.buffer_pool_size = 4;
+        config.eviction_policy = ReplacementPolicy::lru_2();
+
+        // Write scan pages to disk through a separate pool so they can be read back later
+        let scan_ids: Vec<PageId> = {
+            let mut writer = BufferPool::new(file_format.clone(), &config);
+            let ids = (0..6).map(|_| writer.allocate_page(PageType::Data, VersionId(1)).unwrap()).collect();
+            writer.flush_all().unwrap();
+            ids
+        };
+
+        let mut pool = BufferPool::new(file_format.clone(), &config);
+        let hot: Vec<PageId> = (0..2).map(|_| pool.allocate_page(PageType::Data, VersionId(1)).unwrap()).collect();
+        for id in &hot {
+            pool.get_page(*id).unwrap();
+        }
+
+        for id in &scan_ids {
+            pool.get_page_with_hint(*id, AccessHint::UseOnce).unwrap();
+        }
+
+        // The hot pages survive a scan larger than the pool
+        assert!(hot.iter().all(|id| pool.contains_page(*id)));
+
+        let stats = pool.pool_stats();
+        assert_eq!(stats.policy, "lru-k");
+        assert!(stats.evictions >= 4);
+    }
+
+    #[test]
+    fn test_scan_through_manager_leaves_hot_set_resident() {
+        let file_format = create_test_file_format();
+        let mut config = crate::storage_engine::lib::StorageConfig::default();
+        config.buffer_pool_size = 4;
+
+        let scan_ids: Vec<PageId> = {
+            let mut writer = BufferPool::new(file_format.clone(), &config);
+            let ids = (0..8).map(|_| writer.allocate_page(PageType::Data, VersionId(1)).unwrap()).collect();
+            writer.flush_all().unwrap();
+            ids
+        };
+
+        let manager = BufferManager::new(file_format.clone(), &config);
+        let hot: Vec<PageId> = (0..2).map(|_| manager.allocate_page(PageType::Data, VersionId(1)).unwrap()).collect();
+        for id in &hot {
+            manager.get_page(*id).unwrap();
+        }
+
+        for id in &scan_ids {
+            assert_eq!(manager.get_page_for_scan(*id).unwrap().id, *id);
+        }
+
+        // The default policy evicts the scan's own pages first
+        let pool = manager.get_buffer_pool_for_testing().unwrap();
+        assert!(hot.iter().all(|id| pool.contains_page(*id)));
+        assert!(scan_ids.iter().filter(|id| pool.contains_page(**id)).count() <= 2);
+    }
+
+    #[test]
+    fn test_dirty_writeback_counter() {
+        let file_format = create_test_file_format();
+        let mut config = crate::storage_engine::lib::StorageConfig::default();
+        config.buffer_pool_size = 1;
+        let mut pool = BufferPool::new(file_format.clone(), &config);
+
+        let first = pool.allocate_page(PageType::Data, VersionId(1)).unwrap();
+        pool.get_page_mut(first).unwrap().page_mut();
+        pool.allocate_page(PageType::Data, VersionId(1)).unwrap();
+
+        let stats = pool.pool_stats();
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(stats.dirty_writebacks, 1);
+        assert_eq!(stats.resident_pages, 1);
+    }
+
+    fn writeback_config(max_dirty_pages: usize, writer_threads: usize) -> crate::storage_engine::lib::StorageConfig {
+        crate::storage_engine::lib::StorageConfig {
+            flush_interval_ms: 0,
+            max_dirty_pages,
+            writer_threads,
+            ..Default::default()
+        }
+    }
+
+    fn dirty(manager: &BufferManager, page_id: PageId, byte: u8) {
+        let guard = manager.get_page_for_update(page_id).unwrap();
+        let size = guard.page().data.len();
+        guard.update(vec![byte; size]).unwrap();
+    }
+
+    fn wait_for(what: &str, condition: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !condition() {
+            assert!(Instant::now() < deadline, "timed out waiting for {what}");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_writers_start_at_the_start_watermark() {
+        let file_format = create_test_file_format();
+        let manager = BufferManager::new(file_format, &writeback_config(10, 2));
+        let page_ids: Vec<PageId> = (0..10).map(|_| manager.allocate_page(PageType::Data, VersionId(1)).unwrap()).collect();
+
+        for (i, page_id) in page_ids[..5].iter().enumerate() {
+            dirty(&manager, *page_id, i as u8);
+        }
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(manager.dirty_page_count(), 5);
+        assert!(manager.writer_stats().iter().all(|writer| writer.pages_written == 0));
+
+        // The sixth dirty page reaches 60% of max_dirty_pages and wakes the writers
+        dirty(&manager, page_ids[5], 5);
+        wait_for("the writers to flush", || manager.dirty_page_count() == 0);
+
+        let stats = manager.writer_stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats.iter().map(|writer| writer.pages_written).sum::<u64>(), 6);
+        assert!(stats.iter().all(|writer| writer.pages_written > 0 && writer.bytes_written > 0 && writer.write_errors == 0));
+    }
+
+    #[test]
+    fn test_commit_waits_at_the_hard_limit() {
+        let file_format = create_test_file_format();
+        let manager = BufferManager::new(file_format, &writeback_config(20, 1));
+        assert!(manager.throttle_commit().unwrap() < MAX_COMMIT_DELAY);
+
+        {
+            // Dirty pages behind the writer's back so they pile up past the hard limit
+            let mut pool = manager.get_buffer_pool_for_testing().unwrap();
+            pool.set_max_dirty_pages(1000);
+            for _ in 0..20 {
+                let page_id = pool.allocate_page(PageType::Data, VersionId(1)).unwrap();
+                pool.get_page_mut(page_id).unwrap().page_mut();
+            }
+            pool.set_max_dirty_pages(20);
+        }
+
+        manager.throttle_commit().unwrap();
+        assert!(manager.dirty_page_count() < writeback::hard_limit(20));
+    }
+
+    #[test]
+    fn test_writeback_keeps_pages_modified_while_writing_dirty() {
+        let file_format = create_test_file_format();
+        let mut pool = BufferPool::new(file_format, &writeback_config(10, 1));
+        let page_id = pool.allocate_page(PageType::Data, VersionId(1)).unwrap();
+        pool.get_page_mut(page_id).unwrap().page_mut();
+
+        let (_, modifications) = pool.begin_writeback(page_id).unwrap();
+        pool.get_page_mut(page_id).unwrap().page_mut();
+        pool.finish_writeback(page_id, modifications);
+        assert_eq!(pool.dirty_page_count(), 1);
+
+        let (_, modifications) = pool.begin_writeback(page_id).unwrap();
+        pool.finish_writeback(page_id, modifications);
+        assert_eq!(pool.dirty_page_count(), 0);
+        assert!(pool.begin_writeback(page_id).is_none());
+    }
+
+    #[test]
+    fn test_close_flushes_every_dirty_page() {
+        let storage_dir = tempfile::tempdir().unwrap();
+        let config = crate::storage_engine::lib::StorageConfig {
+            path: storage_dir.path().join("test_close.db"),
+            ..writeback_config(1000, 2)
+        };
+        let mut file_format = FileFormat::new(config.clone());
+        file_format.init().unwrap();
+        let file_format = Arc::new(Mutex::new(file_format));
+        let mut manager = BufferManager::new(file_format.clone(), &config);
+        let page_ids: Vec<PageId> = (0..8).map(|_| manager.allocate_page(PageType::Data, VersionId(1)).unwrap()).collect();
+        for (i, page_id) in page_ids.iter().enumerate() {
+            dirty(&manager, *page_id, i as u8 + 1);
+        }
+        assert_eq!(manager.dirty_page_count(), 8);
+
+        manager.close().unwrap();
+        assert_eq!(manager.dirty_page_count(), 0);
+
+        // A fresh pool has to read the pages back from disk
+        let mut pool = BufferPool::new(file_format, &config);
+        for (i, page_id) in page_ids.iter().enumerate() {
+            assert!(pool.get_page(*page_id).unwrap().page().data.iter().all(|&byte| byte == i as u8 + 1));
+        }
+    }
 }
@@ -18,16 +18,38 @@
 //!
 //! This module implements a wait-for graph based deadlock detection system.
 //! It identifies cycles in the transaction dependency graph and resolves
-//! deadlocks by aborting the youngest transaction in the cycle.
-
-use std::collections::{HashMap, HashSet};
+//! deadlocks by aborting one transaction per cycle, chosen by a [`VictimSelector`].
+//! The built-in policies are selected with `StorageConfig::deadlock_policy`.
+//!
+//! Victims are not aborted from under their owner: the detector remembers them and
+//! the owning transaction fails its next operation with [`StorageError::Deadlock`],
+//! which names every transaction in the cycle. The current graph can be inspected
+//! with [`DeadlockDetector::wait_for_graph`], and is published for `dotdb locks`
+//! when a status path is set.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::{Arc, Mutex, RwLock};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
 
 use crate::storage_engine::file_format::PageId;
-use crate::storage_engine::lib::{StorageError, StorageResult};
+use crate::storage_engine::isolation::LockType;
+use crate::storage_engine::lib::{StorageConfig, StorageError, StorageResult};
 use crate::storage_engine::transaction::TransactionId;
 
+/// File the detection service publishes the wait-for graph to, relative to the data directory
+pub const LOCKS_STATUS_FILE: &str = "locks.json";
+
+/// How often the detection service republishes the wait-for graph
+const STATUS_PUBLISH_INTERVAL: Duration = Duration::from_secs(1);
+
 /// Represents a wait-for relationship between transactions
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct WaitForEdge {
@@ -37,21 +59,30 @@ pub struct WaitForEdge {
     pub holder: TransactionId,
     /// Resource being waited for
     pub resource: PageId,
+    /// Lock mode the waiter requested
+    pub lock_mode: LockType,
     /// When this wait relationship was established
     pub wait_start_time: Instant,
 }
 
 impl WaitForEdge {
-    /// Create a new wait-for edge
+    /// Create a new wait-for edge for an exclusive lock request
     pub fn new(waiter: TransactionId, holder: TransactionId, resource: PageId) -> Self {
         Self {
             waiter,
             holder,
             resource,
+            lock_mode: LockType::Exclusive,
             wait_start_time: Instant::now(),
         }
     }
 
+    /// Set the lock mode the waiter requested
+    pub fn with_lock_mode(mut self, lock_mode: LockType) -> Self {
+        self.lock_mode = lock_mode;
+        self
+    }
+
     /// Get the duration this transaction has been waiting
     pub fn wait_duration(&self) -> Duration {
         self.wait_start_time.elapsed()
@@ -86,17 +117,106 @@ impl DeadlockCycle {
     }
 }
 
+/// A transaction chosen to be aborted to break a deadlock
+#[derive(Debug, Clone)]
+pub struct DeadlockVictim {
+    /// The transaction to abort
+    pub transaction: TransactionId,
+    /// The cycle it was chosen from
+    pub cycle: DeadlockCycle,
+}
+
+impl DeadlockVictim {
+    /// Error reported to the victim, naming the other participants of the cycle
+    pub fn to_error(&self) -> StorageError {
+        StorageError::Deadlock {
+            victim: self.transaction,
+            cycle: self.cycle.transactions.clone(),
+            resources: self.cycle.resources.iter().map(|page| page.0).collect(),
+        }
+    }
+}
+
 /// Policy for resolving deadlocks
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum DeadlockResolutionPolicy {
     /// Abort the youngest transaction (highest ID)
+    #[default]
     AbortYoungest,
     /// Abort the oldest transaction (lowest ID)
     AbortOldest,
-    /// Abort the transaction with the least resources
+    /// Abort the transaction holding the fewest locks
     AbortLeastResources,
     /// Abort the transaction that has waited the longest
     AbortLongestWaiting,
+    /// Abort the transaction that has written the fewest bytes
+    AbortLeastWork,
+}
+
+impl DeadlockResolutionPolicy {
+    /// Every built-in policy
+    pub const ALL: [DeadlockResolutionPolicy; 5] = [
+        DeadlockResolutionPolicy::AbortYoungest,
+        DeadlockResolutionPolicy::AbortOldest,
+        DeadlockResolutionPolicy::AbortLeastResources,
+        DeadlockResolutionPolicy::AbortLongestWaiting,
+        DeadlockResolutionPolicy::AbortLeastWork,
+    ];
+
+    /// Name used in configuration and status output
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeadlockResolutionPolicy::AbortYoungest => "youngest",
+            DeadlockResolutionPolicy::AbortOldest => "oldest",
+            DeadlockResolutionPolicy::AbortLeastResources => "fewest-locks",
+            DeadlockResolutionPolicy::AbortLongestWaiting => "longest-waiting",
+            DeadlockResolutionPolicy::AbortLeastWork => "least-work",
+        }
+    }
+}
+
+impl fmt::Display for DeadlockResolutionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for DeadlockResolutionPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter().find(|policy| policy.name() == s).ok_or_else(|| {
+            let names: Vec<&str> = Self::ALL.iter().map(|policy| policy.name()).collect();
+            format!("unknown deadlock victim policy '{s}', expected one of: {}", names.join(", "))
+        })
+    }
+}
+
+/// Chooses which transaction of a deadlock cycle to abort
+pub trait VictimSelector: Send + Sync {
+    /// Pick the victim among `cycle.transactions`
+    fn select_victim(&self, cycle: &DeadlockCycle, graph: &WaitForGraph) -> TransactionId;
+
+    /// Name reported in the wait-for graph snapshot
+    fn name(&self) -> &str {
+        "custom"
+    }
+}
+
+impl VictimSelector for DeadlockResolutionPolicy {
+    fn select_victim(&self, cycle: &DeadlockCycle, graph: &WaitForGraph) -> TransactionId {
+        match self {
+            DeadlockResolutionPolicy::AbortYoungest => cycle.youngest_transaction(),
+            DeadlockResolutionPolicy::AbortOldest => cycle.oldest_transaction(),
+            DeadlockResolutionPolicy::AbortLeastResources => graph.get_least_resources_transaction(&cycle.transactions),
+            DeadlockResolutionPolicy::AbortLongestWaiting => graph.get_longest_waiting_transaction(&cycle.transactions),
+            DeadlockResolutionPolicy::AbortLeastWork => graph.get_least_work_transaction(&cycle.transactions),
+        }
+    }
+
+    fn name(&self) -> &str {
+        DeadlockResolutionPolicy::name(self)
+    }
 }
 
 /// Statistics about deadlock detection
@@ -129,12 +249,25 @@ pub struct WaitForGraph {
 struct TransactionMetadata {
     /// When the transaction started
     start_time: Instant,
-    /// Number of resources held by this transaction
+    /// Number of locks held by this transaction
     resources_held: usize,
+    /// Bytes written by this transaction so far
+    bytes_written: u64,
     /// Total wait time for this transaction
     total_wait_time: Duration,
 }
 
+impl Default for TransactionMetadata {
+    fn default() -> Self {
+        Self {
+            start_time: Instant::now(),
+            resources_held: 0,
+            bytes_written: 0,
+            total_wait_time: Duration::default(),
+        }
+    }
+}
+
 impl WaitForGraph {
     /// Create a new wait-for graph
     pub fn new() -> Self {
@@ -147,18 +280,18 @@ impl WaitForGraph {
 
     /// Add a wait-for edge to the graph
     pub fn add_edge(&mut self, edge: WaitForEdge) {
-        // Add to forward mapping
-        self.edges.entry(edge.waiter).or_default().push(edge.clone());
+        // Add to forward mapping; a retried request keeps its original wait start
+        let edges = self.edges.entry(edge.waiter).or_default();
+        if edges.iter().any(|existing| existing.holder == edge.holder && existing.resource == edge.resource && existing.lock_mode == edge.lock_mode) {
+            return;
+        }
+        edges.push(edge.clone());
 
         // Add to reverse mapping
         self.waiting_for.entry(edge.holder).or_default().insert(edge.waiter);
 
         // Update metadata
-        self.transaction_metadata.entry(edge.waiter).or_insert_with(|| TransactionMetadata {
-            start_time: Instant::now(),
-            resources_held: 0,
-            total_wait_time: Duration::default(),
-        });
+        self.transaction_metadata.entry(edge.waiter).or_default();
     }
 
     /// Remove a wait-for edge from the graph
@@ -180,6 +313,28 @@ impl WaitForGraph {
         }
     }
 
+    /// Remove the edges of a waiter's requests on one resource, e.g. once the lock is granted
+    pub fn remove_waits_on(&mut self, waiter: TransactionId, resource: PageId) {
+        let Some(edges) = self.edges.get_mut(&waiter) else {
+            return;
+        };
+        let removed: Vec<TransactionId> = edges.iter().filter(|edge| edge.resource == resource).map(|edge| edge.holder).collect();
+        edges.retain(|edge| edge.resource != resource);
+        let remaining: HashSet<TransactionId> = edges.iter().map(|edge| edge.holder).collect();
+        if edges.is_empty() {
+            self.edges.remove(&waiter);
+        }
+
+        for holder in removed.into_iter().filter(|holder| !remaining.contains(holder)) {
+            if let Some(waiters) = self.waiting_for.get_mut(&holder) {
+                waiters.remove(&waiter);
+                if waiters.is_empty() {
+                    self.waiting_for.remove(&holder);
+                }
+            }
+        }
+    }
+
     /// Remove all edges involving a transaction
     pub fn remove_transaction(&mut self, txn_id: TransactionId) {
         // Remove all edges where this transaction is waiting
@@ -282,11 +437,34 @@ impl WaitForGraph {
         resources.into_iter().collect()
     }
 
-    /// Update transaction metadata
+    /// Update the number of locks a transaction holds
     pub fn update_transaction_metadata(&mut self, txn_id: TransactionId, resources_held: usize) {
-        if let Some(metadata) = self.transaction_metadata.get_mut(&txn_id) {
-            metadata.resources_held = resources_held;
-        }
+        self.transaction_metadata.entry(txn_id).or_default().resources_held = resources_held;
+    }
+
+    /// Add to the bytes a transaction has written
+    pub fn record_write(&mut self, txn_id: TransactionId, bytes: u64) {
+        self.transaction_metadata.entry(txn_id).or_default().bytes_written += bytes;
+    }
+
+    /// Number of locks a transaction holds, as last reported
+    pub fn locks_held(&self, txn_id: TransactionId) -> usize {
+        self.transaction_metadata.get(&txn_id).map(|m| m.resources_held).unwrap_or(0)
+    }
+
+    /// Bytes a transaction has written so far
+    pub fn bytes_written(&self, txn_id: TransactionId) -> u64 {
+        self.transaction_metadata.get(&txn_id).map(|m| m.bytes_written).unwrap_or(0)
+    }
+
+    /// All edges in the graph
+    pub fn edges(&self) -> impl Iterator<Item = &WaitForEdge> {
+        self.edges.values().flatten()
+    }
+
+    /// Every transaction that is waiting, waited for, or has reported locks or writes
+    pub fn transactions(&self) -> BTreeSet<TransactionId> {
+        self.edges().flat_map(|edge| [edge.waiter, edge.holder]).chain(self.transaction_metadata.keys().copied()).collect()
     }
 
     /// Get transaction with least resources in a cycle
@@ -298,6 +476,15 @@ impl WaitForGraph {
             .unwrap_or(transactions[0])
     }
 
+    /// Get transaction that has written the fewest bytes in a cycle, preferring the youngest on ties
+    pub fn get_least_work_transaction(&self, transactions: &[TransactionId]) -> TransactionId {
+        transactions
+            .iter()
+            .min_by_key(|&&txn_id| (self.bytes_written(txn_id), std::cmp::Reverse(txn_id)))
+            .copied()
+            .unwrap_or(transactions[0])
+    }
+
     /// Get transaction with longest wait time in a cycle
     pub fn get_longest_waiting_transaction(&self, transactions: &[TransactionId]) -> TransactionId {
         transactions
@@ -324,18 +511,80 @@ impl Default for WaitForGraph {
     }
 }
 
+/// A transaction in a wait-for graph snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionLockInfo {
+    pub transaction_id: TransactionId,
+    /// Locks held, as last reported by the lock manager
+    pub locks_held: usize,
+    /// Bytes written so far
+    pub bytes_written: u64,
+    /// Whether the transaction was chosen to break a deadlock and has not finished yet
+    pub deadlock_victim: bool,
+}
+
+/// A waiting-on edge in a wait-for graph snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WaitEdgeInfo {
+    /// Transaction that is waiting
+    pub waiter: TransactionId,
+    /// Transaction holding the conflicting lock
+    pub holder: TransactionId,
+    /// Page the lock was requested on
+    pub resource: u64,
+    /// Lock mode the waiter requested
+    pub lock_mode: LockType,
+    /// How long the waiter has been waiting
+    pub waited_ms: u64,
+}
+
+/// Point-in-time view of the wait-for graph, for debugging lock contention
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WaitForGraphSnapshot {
+    /// Victim selection policy in effect
+    pub policy: String,
+    /// Transactions, ordered by ID
+    pub transactions: Vec<TransactionLockInfo>,
+    /// Waiting-on edges, ordered by waiter then holder
+    pub edges: Vec<WaitEdgeInfo>,
+    /// Cycles currently in the graph
+    pub cycles: Vec<Vec<TransactionId>>,
+    /// Unix time (seconds) the snapshot was taken
+    pub updated_at: u64,
+}
+
+impl WaitForGraphSnapshot {
+    /// Load a snapshot published by [`DeadlockDetector::publish_wait_for_graph`]
+    pub fn read_from(path: &Path) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        serde_json::from_slice(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Write the snapshot atomically, so readers never see a partial file
+    pub fn write_to(&self, path: &Path) -> io::Result<()> {
+        let data = serde_json::to_vec_pretty(self).map_err(io::Error::other)?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, path)
+    }
+}
+
 /// Deadlock detector that monitors the wait-for graph
 pub struct DeadlockDetector {
     /// Wait-for graph
     wait_for_graph: RwLock<WaitForGraph>,
-    /// Detection policy
-    resolution_policy: DeadlockResolutionPolicy,
+    /// Chooses the transaction to abort in each cycle
+    victim_selector: Box<dyn VictimSelector>,
+    /// Victims whose owners have not been told yet
+    victims: Mutex<HashMap<TransactionId, DeadlockVictim>>,
     /// Statistics
     statistics: Mutex<DeadlockStatistics>,
     /// Detection interval
     detection_interval: Duration,
     /// Maximum wait time before forced deadlock check
     max_wait_time: Duration,
+    /// Where to publish the wait-for graph for `dotdb locks`, if anywhere
+    status_path: Option<PathBuf>,
 }
 
 impl DeadlockDetector {
@@ -343,16 +592,39 @@ impl DeadlockDetector {
     pub fn new(resolution_policy: DeadlockResolutionPolicy, detection_interval: Duration, max_wait_time: Duration) -> Self {
         Self {
             wait_for_graph: RwLock::new(WaitForGraph::new()),
-            resolution_policy,
+            victim_selector: Box::new(resolution_policy),
+            victims: Mutex::new(HashMap::new()),
             statistics: Mutex::new(DeadlockStatistics::default()),
             detection_interval,
             max_wait_time,
+            status_path: None,
         }
     }
 
+    /// Create a detector using the victim policy of the storage configuration
+    pub fn for_storage(config: &StorageConfig) -> Self {
+        Self::new(config.deadlock_policy, Duration::from_millis(100), Duration::from_secs(5))
+    }
+
+    /// Replace the victim selection policy
+    pub fn with_victim_selector(mut self, victim_selector: impl VictimSelector + 'static) -> Self {
+        self.victim_selector = Box::new(victim_selector);
+        self
+    }
+
+    /// Publish the wait-for graph to `path` from the detection service
+    pub fn with_status_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.status_path = Some(path.into());
+        self
+    }
+
     /// Add a wait-for relationship
     pub fn add_wait_edge(&self, waiter: TransactionId, holder: TransactionId, resource: PageId) {
-        let edge = WaitForEdge::new(waiter, holder, resource);
+        self.add_lock_wait(WaitForEdge::new(waiter, holder, resource));
+    }
+
+    /// Add a wait-for relationship for a lock request
+    pub fn add_lock_wait(&self, edge: WaitForEdge) {
         let mut graph = self.wait_for_graph.write().unwrap();
         graph.add_edge(edge);
 
@@ -371,7 +643,20 @@ impl DeadlockDetector {
         stats.active_wait_edges = graph.edge_count();
     }
 
+    /// Remove the wait relationships of a waiter's requests on one resource
+    pub fn remove_lock_waits(&self, waiter: TransactionId, resource: PageId) {
+        let mut graph = self.wait_for_graph.write().unwrap();
+        graph.remove_waits_on(waiter, resource);
+
+        // Update statistics
+        let mut stats = self.statistics.lock().unwrap();
+        stats.active_wait_edges = graph.edge_count();
+    }
+
     /// Remove all wait relationships for a transaction
+    ///
+    /// A pending victim stays recorded so its owner still sees the deadlock error;
+    /// use [`take_victim`](Self::take_victim) once the transaction has finished.
     pub fn remove_transaction(&self, txn_id: TransactionId) {
         let mut graph = self.wait_for_graph.write().unwrap();
         graph.remove_transaction(txn_id);
@@ -382,6 +667,9 @@ impl DeadlockDetector {
     }
 
     /// Run deadlock detection and return transactions to abort
+    ///
+    /// Cycles that already contain a pending victim are skipped; they break once
+    /// that victim aborts.
     pub fn detect_and_resolve_deadlocks(&self) -> StorageResult<Vec<TransactionId>> {
         let detection_start = Instant::now();
         let graph = self.wait_for_graph.read().unwrap();
@@ -393,13 +681,18 @@ impl DeadlockDetector {
         }
 
         let mut transactions_to_abort = Vec::new();
+        let mut victims = self.victims.lock().unwrap();
         let mut stats = self.statistics.lock().unwrap();
 
         for deadlock in deadlocks {
+            if deadlock.transactions.iter().any(|txn_id| victims.contains_key(txn_id)) {
+                continue;
+            }
             stats.total_deadlocks_detected += 1;
 
             // Choose transaction to abort based on policy
-            let victim = self.choose_victim(&deadlock, &graph);
+            let victim = self.victim_selector.select_victim(&deadlock, &graph);
+            victims.insert(victim, DeadlockVictim { transaction: victim, cycle: deadlock });
             transactions_to_abort.push(victim);
             stats.total_transactions_aborted += 1;
         }
@@ -415,16 +708,19 @@ impl DeadlockDetector {
         Ok(transactions_to_abort)
     }
 
-    /// Choose victim transaction based on resolution policy
-    fn choose_victim(&self, deadlock: &DeadlockCycle, graph: &WaitForGraph) -> TransactionId {
-        match self.resolution_policy {
-            DeadlockResolutionPolicy::AbortYoungest => deadlock.youngest_transaction(),
-            DeadlockResolutionPolicy::AbortOldest => deadlock.oldest_transaction(),
-            DeadlockResolutionPolicy::AbortLeastResources => graph.get_least_resources_transaction(&deadlock.transactions),
-            DeadlockResolutionPolicy::AbortLongestWaiting => graph.get_longest_waiting_transaction(&deadlock.transactions),
+    /// Fail with the deadlock error if `txn_id` was chosen as a victim
+    pub fn check_victim(&self, txn_id: TransactionId) -> StorageResult<()> {
+        match self.victims.lock().unwrap().get(&txn_id) {
+            Some(victim) => Err(victim.to_error()),
+            None => Ok(()),
         }
     }
 
+    /// Forget a victim once its transaction has finished, returning it if it was one
+    pub fn take_victim(&self, txn_id: TransactionId) -> Option<DeadlockVictim> {
+        self.victims.lock().unwrap().remove(&txn_id)
+    }
+
     /// Check for deadlocks involving long-waiting transactions
     pub fn check_long_waiting_transactions(&self) -> StorageResult<Vec<TransactionId>> {
         let graph = self.wait_for_graph.read().unwrap();
@@ -437,6 +733,7 @@ impl DeadlockDetector {
                 }
             }
         }
+        drop(graph);
 
         if !long_waiters.is_empty() {
             // Force deadlock detection for these transactions
@@ -446,12 +743,70 @@ impl DeadlockDetector {
         }
     }
 
-    /// Update transaction metadata for better deadlock resolution
+    /// Update the number of locks a transaction holds, for the fewest-locks policy
     pub fn update_transaction_metadata(&self, txn_id: TransactionId, resources_held: usize) {
         let mut graph = self.wait_for_graph.write().unwrap();
         graph.update_transaction_metadata(txn_id, resources_held);
     }
 
+    /// Record bytes written by a transaction, for the least-work policy
+    pub fn record_write(&self, txn_id: TransactionId, bytes: u64) {
+        let mut graph = self.wait_for_graph.write().unwrap();
+        graph.record_write(txn_id, bytes);
+    }
+
+    /// Name of the victim selection policy in effect
+    pub fn victim_policy(&self) -> &str {
+        self.victim_selector.name()
+    }
+
+    /// Snapshot of the current wait-for graph
+    pub fn wait_for_graph(&self) -> WaitForGraphSnapshot {
+        let graph = self.wait_for_graph.read().unwrap();
+        let victims = self.victims.lock().unwrap();
+
+        let transactions = graph
+            .transactions()
+            .into_iter()
+            .map(|txn_id| TransactionLockInfo {
+                transaction_id: txn_id,
+                locks_held: graph.locks_held(txn_id),
+                bytes_written: graph.bytes_written(txn_id),
+                deadlock_victim: victims.contains_key(&txn_id),
+            })
+            .collect();
+
+        let mut edges: Vec<WaitEdgeInfo> = graph
+            .edges()
+            .map(|edge| WaitEdgeInfo {
+                waiter: edge.waiter,
+                holder: edge.holder,
+                resource: edge.resource.0,
+                lock_mode: edge.lock_mode,
+                waited_ms: edge.wait_duration().as_millis() as u64,
+            })
+            .collect();
+        edges.sort_by_key(|edge| (edge.waiter, edge.holder, edge.resource));
+
+        WaitForGraphSnapshot {
+            policy: self.victim_selector.name().to_string(),
+            transactions,
+            edges,
+            cycles: graph.detect_deadlocks().into_iter().map(|cycle| cycle.transactions).collect(),
+            updated_at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+        }
+    }
+
+    /// Write the wait-for graph to the status path, if one is set
+    pub fn publish_wait_for_graph(&self) {
+        let Some(path) = &self.status_path else {
+            return;
+        };
+        if let Err(e) = self.wait_for_graph().write_to(path) {
+            warn!("Failed to publish wait-for graph to {}: {}", path.display(), e);
+        }
+    }
+
     /// Get current deadlock statistics
     pub fn get_statistics(&self) -> DeadlockStatistics {
         let stats = self.statistics.lock().unwrap();
@@ -498,12 +853,15 @@ impl Default for DeadlockDetector {
     }
 }
 
+/// Callback invoked with each deadlock victim
+type AbortCallback = dyn Fn(TransactionId) -> StorageResult<()> + Send + Sync;
+
 /// Deadlock detection service that runs periodically
 pub struct DeadlockDetectionService {
     /// The deadlock detector
     detector: Arc<DeadlockDetector>,
     /// Callback to abort transactions
-    abort_callback: Box<dyn Fn(TransactionId) -> StorageResult<()> + Send + Sync>,
+    abort_callback: Arc<AbortCallback>,
     /// Whether the service is running
     is_running: Arc<std::sync::atomic::AtomicBool>,
 }
@@ -516,7 +874,7 @@ impl DeadlockDetectionService {
     {
         Self {
             detector,
-            abort_callback: Box::new(abort_callback),
+            abort_callback: Arc::new(abort_callback),
             is_running: Arc::new(std::sync::atomic::AtomicBool::new(false)),
         }
     }
@@ -530,27 +888,32 @@ impl DeadlockDetectionService {
         self.is_running.store(true, std::sync::atomic::Ordering::Release);
 
         let detector = self.detector.clone();
+        let abort_callback = self.abort_callback.clone();
         let is_running = self.is_running.clone();
         let detection_interval = detector.detection_interval();
 
         std::thread::spawn(move || {
+            let mut last_published: Option<Instant> = None;
             while is_running.load(std::sync::atomic::Ordering::Acquire) {
                 if detector.should_detect_deadlocks() {
-                    if let Ok(victims) = detector.detect_and_resolve_deadlocks() {
-                        for victim in victims {
-                            // In a real implementation, we would call the abort callback here
-                            eprintln!("Deadlock detected: aborting transaction {victim}");
-                        }
-                    }
+                    let mut victims = detector.detect_and_resolve_deadlocks().unwrap_or_default();
 
                     // Also check for long-waiting transactions
-                    if let Ok(long_waiters) = detector.check_long_waiting_transactions() {
-                        for waiter in long_waiters {
-                            eprintln!("Long-waiting transaction detected: {waiter}");
+                    victims.extend(detector.check_long_waiting_transactions().unwrap_or_default());
+
+                    for victim in victims {
+                        warn!("Deadlock detected: aborting transaction {victim}");
+                        if let Err(e) = (*abort_callback)(victim) {
+                            warn!("Failed to abort deadlock victim {victim}: {e}");
                         }
                     }
                 }
 
+                if last_published.is_none_or(|at| at.elapsed() >= STATUS_PUBLISH_INTERVAL) {
+                    detector.publish_wait_for_graph();
+                    last_published = Some(Instant::now());
+                }
+
                 std::thread::sleep(detection_interval);
             }
         });
@@ -787,4 +1150,157 @@ mod tests {
         let deadlocks = graph.detect_deadlocks();
         assert!(deadlocks.len() >= 2); // Should detect both cycles
     }
+
+    /// Long-running analytical transaction 1 and short writer 2 deadlock on two pages
+    fn analytical_vs_writer(policy: DeadlockResolutionPolicy) -> DeadlockDetector {
+        let detector = DeadlockDetector::new(policy, Duration::from_millis(10), Duration::from_secs(1));
+        detector.update_transaction_metadata(1, 40);
+        detector.record_write(1, 64 * 1024);
+        detector.update_transaction_metadata(2, 1);
+        detector.record_write(2, 128);
+        detector.add_lock_wait(WaitForEdge::new(1, 2, PageId(100)).with_lock_mode(LockType::Shared));
+        detector.add_lock_wait(WaitForEdge::new(2, 1, PageId(200)));
+        detector
+    }
+
+    #[test]
+    fn test_victim_policies() {
+        // Transaction 1 started first but 2 is the lighter one
+        let cases = [
+            (DeadlockResolutionPolicy::AbortYoungest, 2),
+            (DeadlockResolutionPolicy::AbortOldest, 1),
+            (DeadlockResolutionPolicy::AbortLeastResources, 2),
+            (DeadlockResolutionPolicy::AbortLeastWork, 2),
+        ];
+        for (policy, expected) in cases {
+            let detector = analytical_vs_writer(policy);
+            assert_eq!(detector.detect_and_resolve_deadlocks().unwrap(), vec![expected], "policy {policy}");
+        }
+
+        // Work, not age, decides under least-work
+        let detector = DeadlockDetector::new(DeadlockResolutionPolicy::AbortLeastWork, Duration::from_millis(10), Duration::from_secs(1));
+        detector.record_write(1, 10);
+        detector.record_write(2, 5000);
+        detector.add_wait_edge(1, 2, PageId(100));
+        detector.add_wait_edge(2, 1, PageId(200));
+        assert_eq!(detector.detect_and_resolve_deadlocks().unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn test_policy_names() {
+        for policy in DeadlockResolutionPolicy::ALL {
+            assert_eq!(policy.name().parse::<DeadlockResolutionPolicy>().unwrap(), policy);
+        }
+        assert_eq!(DeadlockResolutionPolicy::default(), DeadlockResolutionPolicy::AbortYoungest);
+        let error = "random".parse::<DeadlockResolutionPolicy>().unwrap_err();
+        assert!(error.contains("least-work"), "{error}");
+    }
+
+    #[test]
+    fn test_custom_victim_selector() {
+        struct AlwaysFirst;
+        impl VictimSelector for AlwaysFirst {
+            fn select_victim(&self, cycle: &DeadlockCycle, _graph: &WaitForGraph) -> TransactionId {
+                cycle.transactions[0]
+            }
+        }
+
+        let detector = analytical_vs_writer(DeadlockResolutionPolicy::AbortYoungest).with_victim_selector(AlwaysFirst);
+        assert_eq!(detector.victim_policy(), "custom");
+        let victims = detector.detect_and_resolve_deadlocks().unwrap();
+        assert_eq!(victims.len(), 1);
+    }
+
+    #[test]
+    fn test_victim_error_names_cycle() {
+        let detector = analytical_vs_writer(DeadlockResolutionPolicy::AbortLeastWork);
+        assert_eq!(detector.detect_and_resolve_deadlocks().unwrap(), vec![2]);
+
+        assert!(detector.check_victim(1).is_ok());
+        match detector.check_victim(2) {
+            Err(StorageError::Deadlock { victim, mut cycle, mut resources }) => {
+                assert_eq!(victim, 2);
+                cycle.sort();
+                resources.sort();
+                assert_eq!(cycle, vec![1, 2]);
+                assert_eq!(resources, vec![100, 200]);
+            }
+            other => panic!("expected deadlock error, got {other:?}"),
+        }
+        let message = detector.check_victim(2).unwrap_err().to_string();
+        assert!(message.contains("transaction 2") && message.contains("->"), "{message}");
+
+        // The cycle is not resolved twice while the victim is pending
+        assert!(detector.detect_and_resolve_deadlocks().unwrap().is_empty());
+        assert_eq!(detector.get_statistics().total_deadlocks_detected, 1);
+
+        // Once the victim aborts the cycle is gone and it is forgotten
+        detector.remove_transaction(2);
+        assert!(detector.take_victim(2).is_some());
+        assert!(detector.check_victim(2).is_ok());
+        assert!(detector.detect_and_resolve_deadlocks().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_wait_for_graph_snapshot() {
+        let detector = analytical_vs_writer(DeadlockResolutionPolicy::AbortLeastWork);
+        detector.add_lock_wait(WaitForEdge::new(3, 1, PageId(300)).with_lock_mode(LockType::Shared));
+
+        let snapshot = detector.wait_for_graph();
+        assert_eq!(snapshot.policy, "least-work");
+        assert_eq!(snapshot.transactions.iter().map(|t| t.transaction_id).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(snapshot.transactions[0].locks_held, 40);
+        assert_eq!(snapshot.transactions[0].bytes_written, 64 * 1024);
+        assert_eq!(
+            snapshot.edges.iter().map(|e| (e.waiter, e.holder, e.resource, e.lock_mode)).collect::<Vec<_>>(),
+            vec![(1, 2, 100, LockType::Shared), (2, 1, 200, LockType::Exclusive), (3, 1, 300, LockType::Shared)]
+        );
+        assert_eq!(snapshot.cycles.len(), 1);
+        assert!(snapshot.transactions.iter().all(|t| !t.deadlock_victim));
+
+        detector.detect_and_resolve_deadlocks().unwrap();
+        assert!(detector.wait_for_graph().transactions.iter().any(|t| t.transaction_id == 2 && t.deadlock_victim));
+
+        // Round trip through the published file
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(LOCKS_STATUS_FILE);
+        let detector = detector.with_status_path(&path);
+        detector.publish_wait_for_graph();
+        let published = WaitForGraphSnapshot::read_from(&path).unwrap();
+        let edge_keys = |edges: &[WaitEdgeInfo]| edges.iter().map(|e| (e.waiter, e.holder, e.resource, e.lock_mode)).collect::<Vec<_>>();
+        assert_eq!(edge_keys(&published.edges), edge_keys(&detector.wait_for_graph().edges));
+        assert_eq!(published.transactions.len(), 3);
+    }
+
+    #[test]
+    fn test_lock_manager_reports_waits() {
+        use crate::storage_engine::isolation::LockManager;
+
+        let detector = Arc::new(DeadlockDetector::new(DeadlockResolutionPolicy::AbortLeastResources, Duration::from_millis(10), Duration::from_secs(1)));
+        let locks = LockManager::new().with_deadlock_detector(detector.clone());
+
+        assert!(locks.request_lock(1, PageId(100), LockType::Exclusive).unwrap());
+        assert!(locks.request_lock(1, PageId(101), LockType::Exclusive).unwrap());
+        assert!(locks.request_lock(2, PageId(200), LockType::Exclusive).unwrap());
+
+        // Each blocks on the other
+        assert!(!locks.request_lock(1, PageId(200), LockType::Shared).unwrap());
+        assert!(!locks.request_lock(2, PageId(100), LockType::Exclusive).unwrap());
+
+        let snapshot = detector.wait_for_graph();
+        assert_eq!(snapshot.edges.len(), 2);
+        assert_eq!(snapshot.transactions.iter().map(|t| t.locks_held).collect::<Vec<_>>(), vec![2, 1]);
+
+        // Transaction 2 holds fewer locks
+        assert_eq!(detector.detect_and_resolve_deadlocks().unwrap(), vec![2]);
+
+        // Aborting the victim releases its locks and grants transaction 1's queued request
+        locks.release_transaction_locks(2).unwrap();
+        assert!(locks.holds_lock(1, PageId(200), LockType::Shared));
+        assert!(!locks.holds_lock(2, PageId(100), LockType::Exclusive));
+        let snapshot = detector.wait_for_graph();
+        assert!(snapshot.edges.is_empty());
+        assert!(snapshot.cycles.is_empty());
+        assert_eq!(locks.get_statistics().total_waiting_requests, 0);
+    }
 }
@@ -0,0 +1,496 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+// Page encryption module
+// This module encrypts pages at rest with AES-256-GCM. Each page is sealed with a data encryption key (DEK) that is stored in the file header wrapped by a master key, which never touches the disk: it is supplied through the environment, a key file or a key management service callback. Rotating the key activates a new DEK for all writes and lets a background task re-encrypt the remaining pages, tracking its progress in the file header.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use tracing::{info, warn};
+
+use crate::storage_engine::file_format::{FileFormat, PageId};
+use crate::storage_engine::lib::{StorageError, StorageResult};
+
+/// Environment variable holding the master key as 64 hex digits
+pub const MASTER_KEY_ENV: &str = "DOTDB_MASTER_KEY";
+/// Environment variable naming a file that holds the master key
+pub const MASTER_KEY_FILE_ENV: &str = "DOTDB_MASTER_KEY_FILE";
+
+/// Size of data encryption and master keys in bytes
+pub const KEY_SIZE: usize = 32;
+/// Size of an AES-GCM nonce in bytes
+pub const NONCE_SIZE: usize = 12;
+/// Size of the AES-GCM authentication tag stored after each encrypted page's data
+pub const TAG_SIZE: usize = 16;
+
+/// Associated data binding a wrapped DEK to its purpose
+const WRAP_AAD: &[u8] = b"dotdb data encryption key";
+
+/// A data encryption key, zeroed when dropped
+pub struct DataKey([u8; KEY_SIZE]);
+
+impl DataKey {
+    /// Generate a random key
+    pub fn generate() -> Self {
+        Self(rand::random())
+    }
+
+    pub fn from_bytes(bytes: [u8; KEY_SIZE]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; KEY_SIZE] {
+        &self.0
+    }
+}
+
+impl Drop for DataKey {
+    fn drop(&mut self) {
+        self.0.fill(0);
+    }
+}
+
+impl fmt::Debug for DataKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DataKey(..)")
+    }
+}
+
+/// Wraps data encryption keys for storage in the file header and unwraps them on open
+///
+/// Implementations hold or reach the master key; the storage file only ever sees the
+/// wrapped form.
+pub trait KeyProvider: Send + Sync + fmt::Debug {
+    /// Encrypt a DEK under the master key
+    fn wrap_key(&self, key: &DataKey) -> StorageResult<Vec<u8>>;
+
+    /// Decrypt a DEK produced by [`wrap_key`](Self::wrap_key)
+    fn unwrap_key(&self, wrapped: &[u8]) -> StorageResult<DataKey>;
+}
+
+/// A master key held in memory, wrapping DEKs with AES-256-GCM
+pub struct MasterKey([u8; KEY_SIZE]);
+
+impl MasterKey {
+    pub fn from_bytes(bytes: [u8; KEY_SIZE]) -> Self {
+        Self(bytes)
+    }
+
+    /// Parse a key written as 64 hex digits
+    pub fn from_hex(hex_key: &str) -> StorageResult<Self> {
+        let bytes = hex::decode(hex_key.trim()).map_err(|e| invalid_master_key(format!("not valid hex: {e}")))?;
+        let bytes: [u8; KEY_SIZE] = bytes
+            .try_into()
+            .map_err(|bytes: Vec<u8>| invalid_master_key(format!("expected {KEY_SIZE} bytes, got {}", bytes.len())))?;
+        Ok(Self(bytes))
+    }
+
+    /// Read a key file holding either 64 hex digits or the 32 raw key bytes
+    pub fn from_file(path: &Path) -> StorageResult<Self> {
+        let contents = std::fs::read(path)?;
+        match <[u8; KEY_SIZE]>::try_from(contents.as_slice()) {
+            Ok(bytes) if std::str::from_utf8(&bytes).is_err() => Ok(Self(bytes)),
+            _ => Self::from_hex(&String::from_utf8_lossy(&contents)),
+        }
+    }
+
+    /// The key from [`MASTER_KEY_ENV`], or from the file named by [`MASTER_KEY_FILE_ENV`];
+    /// `None` if neither is set
+    pub fn from_env() -> StorageResult<Option<Self>> {
+        if let Ok(hex_key) = std::env::var(MASTER_KEY_ENV) {
+            return Self::from_hex(&hex_key).map(Some);
+        }
+        match std::env::var(MASTER_KEY_FILE_ENV) {
+            Ok(path) => Self::from_file(Path::new(&path)).map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+impl Drop for MasterKey {
+    fn drop(&mut self) {
+        self.0.fill(0);
+    }
+}
+
+impl fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MasterKey(..)")
+    }
+}
+
+impl KeyProvider for MasterKey {
+    /// The wrapped key is the nonce followed by the encrypted key and its tag
+    fn wrap_key(&self, key: &DataKey) -> StorageResult<Vec<u8>> {
+        let nonce: [u8; NONCE_SIZE] = rand::random();
+        let cipher = Aes256Gcm::new((&self.0).into());
+        let sealed = cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: key.as_bytes(), aad: WRAP_AAD })
+            .map_err(|_| StorageError::Encryption("failed to wrap data encryption key".to_string()))?;
+        Ok([nonce.as_slice(), &sealed].concat())
+    }
+
+    fn unwrap_key(&self, wrapped: &[u8]) -> StorageResult<DataKey> {
+        if wrapped.len() != NONCE_SIZE + KEY_SIZE + TAG_SIZE {
+            return Err(StorageError::Encryption(format!("wrapped data encryption key has {} bytes", wrapped.len())));
+        }
+        let (nonce, sealed) = wrapped.split_at(NONCE_SIZE);
+        let cipher = Aes256Gcm::new((&self.0).into());
+        let mut key = cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: sealed, aad: WRAP_AAD })
+            .map_err(|_| StorageError::Encryption("the master key does not unwrap the data encryption keys; is it the key this database was encrypted with?".to_string()))?;
+        let bytes: [u8; KEY_SIZE] = key.as_slice().try_into().expect("unwrapped key has the wrapped length");
+        key.fill(0);
+        Ok(DataKey(bytes))
+    }
+}
+
+type WrapFn = dyn Fn(&[u8]) -> Result<Vec<u8>, String> + Send + Sync;
+
+/// Delegates wrapping to callbacks, typically calls to a key management service that
+/// holds the master key
+pub struct CallbackKeyProvider {
+    wrap: Box<WrapFn>,
+    unwrap: Box<WrapFn>,
+}
+
+impl CallbackKeyProvider {
+    /// `wrap` encrypts raw DEK bytes and `unwrap` reverses it; errors are reported as
+    /// encryption errors
+    pub fn new(wrap: impl Fn(&[u8]) -> Result<Vec<u8>, String> + Send + Sync + 'static, unwrap: impl Fn(&[u8]) -> Result<Vec<u8>, String> + Send + Sync + 'static) -> Self {
+        Self {
+            wrap: Box::new(wrap),
+            unwrap: Box::new(unwrap),
+        }
+    }
+}
+
+impl fmt::Debug for CallbackKeyProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CallbackKeyProvider")
+    }
+}
+
+impl KeyProvider for CallbackKeyProvider {
+    fn wrap_key(&self, key: &DataKey) -> StorageResult<Vec<u8>> {
+        (self.wrap)(key.as_bytes()).map_err(|e| StorageError::Encryption(format!("key provider failed to wrap data encryption key: {e}")))
+    }
+
+    fn unwrap_key(&self, wrapped: &[u8]) -> StorageResult<DataKey> {
+        let mut key = (self.unwrap)(wrapped).map_err(|e| StorageError::Encryption(format!("key provider failed to unwrap data encryption key: {e}")))?;
+        let bytes = <[u8; KEY_SIZE]>::try_from(key.as_slice()).map_err(|_| StorageError::Encryption(format!("key provider returned a {}-byte data encryption key", key.len())));
+        key.fill(0);
+        bytes.map(DataKey)
+    }
+}
+
+fn invalid_master_key(reason: String) -> StorageError {
+    StorageError::InvalidConfig {
+        setting: "master key".to_string(),
+        reason,
+    }
+}
+
+/// A wrapped DEK as recorded in the file header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrappedKey {
+    /// Key ID stamped on the pages it encrypts; never 0, which marks plaintext pages
+    pub id: u8,
+    /// When the key was activated, in nanoseconds since the Unix epoch
+    pub created_at: u64,
+    pub wrapped: Vec<u8>,
+}
+
+/// Progress of re-encrypting pages with the active key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RotationProgress {
+    /// Next page to visit
+    pub next_page: u64,
+    /// Pages rewritten with the active key so far
+    pub pages_rewritten: u64,
+    /// Pages that could not be re-encrypted: corrupt pages, and plaintext pages too full
+    /// to hold the authentication tag. Retired keys are kept while any page was skipped.
+    pub pages_skipped: u64,
+}
+
+/// Outcome of one batch of re-encryption
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReencryptionBatch {
+    /// Progress of the rotation after the batch
+    pub progress: RotationProgress,
+    /// Whether the batch visited the last page
+    pub finished: bool,
+}
+
+/// Encryption state recorded in the file header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptionHeader {
+    /// Key new pages are written with
+    pub active_key: u8,
+    /// Every key pages may still be encrypted with, the active one included
+    pub keys: Vec<WrappedKey>,
+    /// Set while pages encrypted with older keys, or not at all, remain
+    pub rotation: Option<RotationProgress>,
+}
+
+/// Encryption state of a storage file, readable without the master key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptionStatus {
+    /// Pages in the file, excluding the file header
+    pub total_pages: u64,
+    /// `None` for an unencrypted file
+    pub encryption: Option<EncryptionHeader>,
+}
+
+impl EncryptionStatus {
+    pub fn is_encrypted(&self) -> bool {
+        self.encryption.is_some()
+    }
+
+    /// Percentage of pages visited by the running re-encryption, `None` when none is running
+    pub fn rotation_percent(&self) -> Option<f64> {
+        let rotation = self.encryption.as_ref()?.rotation?;
+        let visited = rotation.next_page.saturating_sub(1);
+        Some(if self.total_pages == 0 { 100.0 } else { visited as f64 * 100.0 / self.total_pages as f64 })
+    }
+}
+
+/// Unwrapped DEKs of an encrypted file, ready to seal and open pages
+pub(crate) struct Keyring {
+    active: u8,
+    ciphers: HashMap<u8, Aes256Gcm>,
+}
+
+impl Keyring {
+    /// Keyring of a file just encrypted with its first key
+    pub(crate) fn new(id: u8, key: &DataKey) -> Self {
+        Self {
+            active: id,
+            ciphers: HashMap::from([(id, Aes256Gcm::new(key.as_bytes().into()))]),
+        }
+    }
+
+    /// Unwrap every key in the header
+    pub(crate) fn unwrap(header: &EncryptionHeader, provider: &dyn KeyProvider) -> StorageResult<Self> {
+        let mut ciphers = HashMap::new();
+        for key in &header.keys {
+            let data_key = provider.unwrap_key(&key.wrapped)?;
+            ciphers.insert(key.id, Aes256Gcm::new(data_key.as_bytes().into()));
+        }
+        if !ciphers.contains_key(&header.active_key) {
+            return Err(StorageError::Corruption(format!("active encryption key {} is missing from the keyring", header.active_key)));
+        }
+        Ok(Self { active: header.active_key, ciphers })
+    }
+
+    pub(crate) fn active(&self) -> u8 {
+        self.active
+    }
+
+    pub(crate) fn insert(&mut self, id: u8, key: &DataKey) {
+        self.ciphers.insert(id, Aes256Gcm::new(key.as_bytes().into()));
+        self.active = id;
+    }
+
+    pub(crate) fn retain(&mut self, id: u8) {
+        self.ciphers.retain(|&key_id, _| key_id == id);
+    }
+
+    /// Encrypt page data with the active key, returning the nonce and the ciphertext
+    /// followed by its tag
+    pub(crate) fn seal(&self, page_id: PageId, aad: &[u8], data: &[u8]) -> StorageResult<([u8; NONCE_SIZE], Vec<u8>)> {
+        let nonce: [u8; NONCE_SIZE] = rand::random();
+        let cipher = &self.ciphers[&self.active];
+        let sealed = cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: data, aad })
+            .map_err(|_| StorageError::Encryption(format!("failed to encrypt page {}", page_id.0)))?;
+        Ok((nonce, sealed))
+    }
+
+    /// Decrypt page data sealed with `key_id`
+    pub(crate) fn open(&self, page_id: PageId, key_id: u8, nonce: &[u8; NONCE_SIZE], aad: &[u8], sealed: &[u8]) -> StorageResult<Vec<u8>> {
+        let cipher = self
+            .ciphers
+            .get(&key_id)
+            .ok_or_else(|| StorageError::Encryption(format!("page {} is encrypted with key {key_id}, which is not in the keyring", page_id.0)))?;
+        cipher.decrypt(Nonce::from_slice(nonce), Payload { msg: sealed, aad }).map_err(|_| {
+            StorageError::Encryption(format!(
+                "page {} failed authentication under key {key_id}: its checksum matches, so the data was altered deliberately or the wrong key was supplied",
+                page_id.0
+            ))
+        })
+    }
+}
+
+/// Configuration for background re-encryption
+#[derive(Debug, Clone)]
+pub struct ReencryptionConfig {
+    /// Pages rewritten per batch; the storage file is locked for one batch at a time
+    pub batch_pages: u64,
+    /// Pause between batches
+    pub interval: Duration,
+}
+
+impl Default for ReencryptionConfig {
+    fn default() -> Self {
+        Self {
+            batch_pages: 64,
+            interval: Duration::from_millis(100),
+        }
+    }
+}
+
+/// Re-encrypts pages with the active key on a background thread, a batch at a time,
+/// whenever a rotation is in progress
+pub struct ReencryptionService {
+    file_format: Arc<Mutex<FileFormat>>,
+    config: ReencryptionConfig,
+    shutdown: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ReencryptionService {
+    /// Creates a service for the storage file. Call [`start`](Self::start) to begin.
+    pub fn new(file_format: Arc<Mutex<FileFormat>>, config: ReencryptionConfig) -> Self {
+        Self {
+            file_format,
+            config,
+            shutdown: Arc::new(AtomicBool::new(false)),
+            handle: None,
+        }
+    }
+
+    /// Starts the re-encryption thread.
+    pub fn start(&mut self) -> std::io::Result<()> {
+        if self.handle.is_some() {
+            return Ok(());
+        }
+        self.shutdown.store(false, Ordering::Release);
+
+        let file_format = Arc::clone(&self.file_format);
+        let shutdown = Arc::clone(&self.shutdown);
+        let config = self.config.clone();
+        let handle = thread::Builder::new().name("page-reencrypt".to_string()).spawn(move || {
+            while !shutdown.load(Ordering::Relaxed) {
+                let result = match file_format.lock() {
+                    Ok(mut file_format) => file_format.reencrypt_pages(config.batch_pages),
+                    Err(_) => return,
+                };
+                match result {
+                    Ok(Some(ReencryptionBatch { progress, finished: true })) => {
+                        info!("Re-encryption finished: {} pages rewritten, {} skipped", progress.pages_rewritten, progress.pages_skipped)
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("Re-encryption batch failed: {e}"),
+                }
+                thread::sleep(config.interval);
+            }
+        })?;
+        self.handle = Some(handle);
+        Ok(())
+    }
+
+    /// Signals the re-encryption thread to stop and waits for it.
+    pub fn stop(&mut self) {
+        let Some(handle) = self.handle.take() else {
+            return;
+        };
+        self.shutdown.store(true, Ordering::Release);
+        let _ = handle.join();
+    }
+}
+
+impl Drop for ReencryptionService {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_master_key_wraps_and_unwraps() {
+        let master = MasterKey::from_bytes([9; KEY_SIZE]);
+        let key = DataKey::generate();
+        let wrapped = master.wrap_key(&key).unwrap();
+        assert_eq!(wrapped.len(), NONCE_SIZE + KEY_SIZE + TAG_SIZE);
+        assert!(!wrapped.windows(KEY_SIZE).any(|window| window == key.as_bytes()));
+        assert_eq!(master.unwrap_key(&wrapped).unwrap().as_bytes(), key.as_bytes());
+
+        let other = MasterKey::from_bytes([8; KEY_SIZE]);
+        assert!(matches!(other.unwrap_key(&wrapped), Err(StorageError::Encryption(_))));
+    }
+
+    #[test]
+    fn test_master_key_sources() {
+        let hex_key = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff";
+        assert_eq!(MasterKey::from_hex(hex_key).unwrap().0[1], 0x11);
+        assert!(matches!(MasterKey::from_hex("abcd"), Err(StorageError::InvalidConfig { .. })));
+        assert!(matches!(MasterKey::from_hex("not hex"), Err(StorageError::InvalidConfig { .. })));
+
+        let dir = tempdir().unwrap();
+        let hex_file = dir.path().join("master.hex");
+        std::fs::write(&hex_file, format!("{hex_key}\n")).unwrap();
+        assert_eq!(MasterKey::from_file(&hex_file).unwrap().0[31], 0xff);
+
+        let raw_file = dir.path().join("master.key");
+        let mut raw = [0xEE; KEY_SIZE];
+        raw[0] = 0x80;
+        std::fs::write(&raw_file, raw).unwrap();
+        assert_eq!(MasterKey::from_file(&raw_file).unwrap().0, raw);
+    }
+
+    #[test]
+    fn test_callback_provider_delegates_wrapping() {
+        // Stands in for a key management service holding the master key
+        let service = Arc::new(MasterKey::from_bytes([6; KEY_SIZE]));
+        let (wrapper, unwrapper) = (Arc::clone(&service), Arc::clone(&service));
+        let provider = CallbackKeyProvider::new(
+            move |key| wrapper.wrap_key(&DataKey::from_bytes(key.try_into().unwrap())).map_err(|e| e.to_string()),
+            move |wrapped| unwrapper.unwrap_key(wrapped).map(|key| key.as_bytes().to_vec()).map_err(|e| e.to_string()),
+        );
+
+        let key = DataKey::generate();
+        let wrapped = provider.wrap_key(&key).unwrap();
+        assert_eq!(provider.unwrap_key(&wrapped).unwrap().as_bytes(), key.as_bytes());
+
+        let failing = CallbackKeyProvider::new(|_| Err("service unavailable".to_string()), |_| Ok(vec![0; 4]));
+        assert!(failing.wrap_key(&key).unwrap_err().to_string().contains("service unavailable"));
+        assert!(matches!(failing.unwrap_key(&wrapped), Err(StorageError::Encryption(_))));
+    }
+
+    #[test]
+    fn test_sealed_pages_are_bound_to_their_page() {
+        let keyring = Keyring::new(1, &DataKey::generate());
+        let (nonce, sealed) = keyring.seal(PageId(3), b"page 3", b"payload").unwrap();
+        assert_eq!(sealed.len(), b"payload".len() + TAG_SIZE);
+        assert_eq!(keyring.open(PageId(3), 1, &nonce, b"page 3", &sealed).unwrap(), b"payload");
+
+        assert!(keyring.open(PageId(4), 1, &nonce, b"page 4", &sealed).is_err());
+        assert!(keyring.open(PageId(3), 2, &nonce, b"page 3", &sealed).unwrap_err().to_string().contains("not in the keyring"));
+    }
+}
@@ -0,0 +1,443 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Storage engine with online reconfiguration
+//!
+//! A [`StorageEngine`] owns the storage file and buffer pool opened from a
+//! [`StorageConfig`]. Settings that only tune the running engine can be changed in
+//! place with [`StorageEngine::apply_config_delta`]: the flush interval, the dirty
+//! page threshold, the compaction IO budget and the buffer pool size, which may
+//! grow but not shrink. Settings that shape the files on disk or are only read at
+//! startup, such as `page_size` and `path`, are rejected with
+//! [`StorageError::RestartRequired`].
+//!
+//! An engine opened with [`StorageEngine::open_encrypted`] encrypts pages at rest and
+//! re-encrypts them in the background after [`StorageEngine::rotate_key`].
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use tracing::info;
+
+use crate::compaction::scheduler::{CompactionScheduler, IoThrottle};
+use crate::storage_engine::buffer_manager::BufferManager;
+use crate::storage_engine::encryption::{EncryptionStatus, KeyProvider, ReencryptionConfig, ReencryptionService};
+use crate::storage_engine::file_format::FileFormat;
+use crate::storage_engine::lib::{StorageConfig, StorageError, StorageResult};
+
+/// Settings that are only read when the engine starts
+const BOOT_ONLY_SETTINGS: &[&str] = &[
+    "path",
+    "page_size",
+    "eviction_policy",
+    "direct_io",
+    "io_backend",
+    "wal_size",
+    "writer_threads",
+    "compaction_threads",
+    "deadlock_policy",
+];
+
+/// Requested changes to a running engine's configuration; `None` leaves a setting as it is
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StorageConfigDelta {
+    /// Background flush interval, 0 to stop background flushing
+    pub flush_interval_ms: Option<u64>,
+    pub max_dirty_pages: Option<usize>,
+    /// Compaction IO budget, 0 for unlimited
+    pub compaction_io_budget_bytes_per_sec: Option<u64>,
+    /// New buffer pool capacity in pages, at least the current one
+    pub buffer_pool_size: Option<usize>,
+    /// Boot-only: a delta setting it is rejected
+    pub page_size: Option<usize>,
+    /// Boot-only: a delta setting it is rejected
+    pub path: Option<PathBuf>,
+}
+
+impl StorageConfigDelta {
+    /// Add a change given as a key and value, as in `storage.flush_interval_ms = 500`
+    ///
+    /// The `storage.` prefix is optional. Boot-only settings other than `page_size` and
+    /// `path` are rejected here, since the delta has no field for them.
+    pub fn set(&mut self, key: &str, value: &str) -> StorageResult<()> {
+        let setting = key.strip_prefix("storage.").unwrap_or(key);
+        let value = value.trim();
+        match setting {
+            "flush_interval_ms" => self.flush_interval_ms = Some(parse(setting, value)?),
+            "max_dirty_pages" => self.max_dirty_pages = Some(parse(setting, value)?),
+            "compaction_io_budget_bytes_per_sec" => self.compaction_io_budget_bytes_per_sec = Some(parse(setting, value)?),
+            "buffer_pool_size" => self.buffer_pool_size = Some(parse(setting, value)?),
+            "page_size" => self.page_size = Some(parse(setting, value)?),
+            "path" => self.path = Some(PathBuf::from(value)),
+            _ if BOOT_ONLY_SETTINGS.contains(&setting) => return Err(StorageError::RestartRequired { setting: setting.to_string() }),
+            _ => {
+                return Err(StorageError::InvalidConfig {
+                    setting: setting.to_string(),
+                    reason: "not a storage setting".to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the delta changes nothing
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+fn parse<T: std::str::FromStr>(setting: &str, value: &str) -> StorageResult<T> {
+    value.parse().map_err(|_| StorageError::InvalidConfig {
+        setting: setting.to_string(),
+        reason: format!("'{value}' is not a non-negative integer"),
+    })
+}
+
+/// A setting changed by [`StorageEngine::apply_config_delta`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedConfigChange {
+    pub setting: &'static str,
+    pub old_value: String,
+    pub new_value: String,
+}
+
+/// Storage file, buffer pool and the configuration they run with
+pub struct StorageEngine {
+    config: Mutex<StorageConfig>,
+    buffer_manager: Mutex<BufferManager>,
+    /// Storage file shared with the buffer pool
+    file_format: Arc<Mutex<FileFormat>>,
+    /// Throttle of the compaction scheduler running over this engine's files, if any
+    compaction_throttle: Mutex<Option<Arc<IoThrottle>>>,
+    /// Background re-encryption, running while the storage file is encrypted
+    reencryption: Mutex<Option<ReencryptionService>>,
+}
+
+impl std::fmt::Debug for StorageEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StorageEngine").field("config", &*lock(&self.config)).finish_non_exhaustive()
+    }
+}
+
+impl StorageEngine {
+    /// Open or create the storage file at `config.path`
+    ///
+    /// An existing file keeps the page size it was created with.
+    pub fn open(config: StorageConfig) -> StorageResult<Self> {
+        Self::open_file(config, None)
+    }
+
+    /// Open or create the storage file at `config.path` with the master key of its
+    /// encryption
+    ///
+    /// A new file is created encrypted; an unencrypted one stays so until
+    /// [`enable_encryption`](Self::enable_encryption). Pages still to be re-encrypted
+    /// after a key rotation are rewritten in the background while the engine runs.
+    pub fn open_encrypted(config: StorageConfig, key_provider: Arc<dyn KeyProvider>) -> StorageResult<Self> {
+        Self::open_file(config, Some(key_provider))
+    }
+
+    fn open_file(mut config: StorageConfig, key_provider: Option<Arc<dyn KeyProvider>>) -> StorageResult<Self> {
+        let mut file_format = FileFormat::new(config.clone());
+        if let Some(key_provider) = key_provider {
+            file_format.set_key_provider(key_provider);
+        }
+        file_format.init()?;
+        config.page_size = file_format.page_size();
+        let encrypted = file_format.is_encrypted();
+
+        let file_format = Arc::new(Mutex::new(file_format));
+        let buffer_manager = BufferManager::new(Arc::clone(&file_format), &config);
+        let engine = Self {
+            config: Mutex::new(config),
+            buffer_manager: Mutex::new(buffer_manager),
+            file_format,
+            compaction_throttle: Mutex::new(None),
+            reencryption: Mutex::new(None),
+        };
+        if encrypted {
+            engine.start_reencryption()?;
+        }
+        Ok(engine)
+    }
+
+    /// Encryption state of the storage file, including re-encryption progress
+    pub fn encryption_status(&self) -> EncryptionStatus {
+        lock(&self.file_format).encryption_status()
+    }
+
+    /// Encrypt an unencrypted storage file in place; existing pages are encrypted in the
+    /// background. Returns the ID of the new data encryption key.
+    pub fn enable_encryption(&self) -> StorageResult<u8> {
+        let key_id = lock(&self.file_format).enable_encryption()?;
+        self.start_reencryption()?;
+        Ok(key_id)
+    }
+
+    /// Activate a new data encryption key; pages written with older keys are
+    /// re-encrypted in the background. Returns the ID of the new key.
+    pub fn rotate_key(&self) -> StorageResult<u8> {
+        lock(&self.file_format).rotate_key()
+    }
+
+    fn start_reencryption(&self) -> StorageResult<()> {
+        let mut reencryption = lock(&self.reencryption);
+        if reencryption.is_none() {
+            let mut service = ReencryptionService::new(Arc::clone(&self.file_format), ReencryptionConfig::default());
+            service.start()?;
+            *reencryption = Some(service);
+        }
+        Ok(())
+    }
+
+    /// Route compaction IO budget changes to a running scheduler's throttle
+    pub fn attach_compaction_throttle(&self, throttle: Arc<IoThrottle>) {
+        throttle.set_budget_bytes_per_sec(lock(&self.config).compaction_io_budget_bytes_per_sec);
+        *lock(&self.compaction_throttle) = Some(throttle);
+    }
+
+    /// Run alongside a compaction scheduler sharing this engine's disk: its IO budget
+    /// follows this engine's configuration, and it backs off while page writes are slow
+    pub fn attach_compaction(&self, scheduler: &CompactionScheduler) {
+        self.attach_compaction_throttle(scheduler.io_throttle());
+        lock(&self.file_format).set_write_latency_monitor(scheduler.latency_monitor());
+    }
+
+    /// The configuration currently in effect
+    pub fn config(&self) -> StorageConfig {
+        lock(&self.config).clone()
+    }
+
+    pub fn buffer_manager(&self) -> MutexGuard<'_, BufferManager> {
+        lock(&self.buffer_manager)
+    }
+
+    /// Stop background re-encryption and flush every dirty page to the storage file
+    ///
+    /// All pages dirty when `close` is called are on disk once it returns.
+    pub fn close(&self) -> StorageResult<()> {
+        if let Some(mut service) = lock(&self.reencryption).take() {
+            service.stop();
+        }
+        lock(&self.buffer_manager).close()
+    }
+
+    /// Validate `delta` and apply it to the running engine
+    ///
+    /// Either every setting in the delta is applied or, if any is invalid or boot-only,
+    /// none is. Settings already at the requested value are not reported. Each applied
+    /// change is logged with its old and new value.
+    pub fn apply_config_delta(&self, delta: &StorageConfigDelta) -> StorageResult<Vec<AppliedConfigChange>> {
+        if delta.path.is_some() {
+            return Err(StorageError::RestartRequired { setting: "path".to_string() });
+        }
+        if delta.page_size.is_some() {
+            return Err(StorageError::RestartRequired { setting: "page_size".to_string() });
+        }
+
+        let mut config = lock(&self.config);
+        if delta.max_dirty_pages == Some(0) {
+            return Err(StorageError::InvalidConfig {
+                setting: "max_dirty_pages".to_string(),
+                reason: "must be at least 1".to_string(),
+            });
+        }
+        if let Some(size) = delta.buffer_pool_size
+            && size < config.buffer_pool_size
+        {
+            return Err(StorageError::InvalidConfig {
+                setting: "buffer_pool_size".to_string(),
+                reason: format!(
+                    "the buffer pool can grow but not shrink while running ({} < {} pages); restart to shrink it",
+                    size, config.buffer_pool_size
+                ),
+            });
+        }
+
+        let mut changes = Vec::new();
+        let mut buffer_manager = lock(&self.buffer_manager);
+        if let Some(size) = delta.buffer_pool_size.filter(|&size| size != config.buffer_pool_size) {
+            buffer_manager.grow_pool(size)?;
+            changes.push(change("buffer_pool_size", config.buffer_pool_size, size));
+            config.buffer_pool_size = size;
+        }
+        if let Some(max) = delta.max_dirty_pages.filter(|&max| max != config.max_dirty_pages) {
+            buffer_manager.set_max_dirty_pages(max)?;
+            changes.push(change("max_dirty_pages", config.max_dirty_pages, max));
+            config.max_dirty_pages = max;
+        }
+        if let Some(interval) = delta.flush_interval_ms.filter(|&interval| interval != config.flush_interval_ms) {
+            buffer_manager.set_flush_interval(Duration::from_millis(interval))?;
+            changes.push(change("flush_interval_ms", config.flush_interval_ms, interval));
+            config.flush_interval_ms = interval;
+        }
+        if let Some(budget) = delta.compaction_io_budget_bytes_per_sec.filter(|&budget| budget != config.compaction_io_budget_bytes_per_sec) {
+            if let Some(throttle) = lock(&self.compaction_throttle).as_ref() {
+                throttle.set_budget_bytes_per_sec(budget);
+            }
+            changes.push(change("compaction_io_budget_bytes_per_sec", config.compaction_io_budget_bytes_per_sec, budget));
+            config.compaction_io_budget_bytes_per_sec = budget;
+        }
+
+        for change in &changes {
+            info!(setting = change.setting, old_value = %change.old_value, new_value = %change.new_value, "Applied storage config change");
+        }
+        Ok(changes)
+    }
+}
+
+fn change(setting: &'static str, old_value: impl ToString, new_value: impl ToString) -> AppliedConfigChange {
+    AppliedConfigChange {
+        setting,
+        old_value: old_value.to_string(),
+        new_value: new_value.to_string(),
+    }
+}
+
+/// Lock a mutex, recovering the data if a panicking thread poisoned it
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compaction::scheduler::{CompactionIo, CompactionSchedulerConfig, CompactionSource};
+    use crate::compaction::strategy::CompactionTask;
+    use crate::fs::FileMetadata;
+    use crate::storage_engine::encryption::MasterKey;
+    use crate::storage_engine::file_format::PageType;
+    use crate::storage_engine::lib::VersionId;
+    use tempfile::tempdir;
+
+    fn engine(dir: &std::path::Path) -> StorageEngine {
+        StorageEngine::open(StorageConfig {
+            path: dir.join("engine.db"),
+            buffer_pool_size: 100,
+            ..StorageConfig::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_live_settings_apply_in_place() {
+        let dir = tempdir().unwrap();
+        let engine = engine(dir.path());
+        let throttle = Arc::new(IoThrottle::new(0, Duration::from_secs(1)));
+        engine.attach_compaction_throttle(Arc::clone(&throttle));
+
+        let mut delta = StorageConfigDelta::default();
+        delta.set("storage.flush_interval_ms", "500").unwrap();
+        delta.set("max_dirty_pages", "64").unwrap();
+        delta.set("storage.buffer_pool_size", "250").unwrap();
+        delta.set("storage.compaction_io_budget_bytes_per_sec", "1048576").unwrap();
+        let changes = engine.apply_config_delta(&delta).unwrap();
+
+        assert_eq!(changes.len(), 4);
+        assert!(changes.contains(&change("flush_interval_ms", 1000, 500)));
+        assert!(changes.contains(&change("buffer_pool_size", 100, 250)));
+        assert_eq!(engine.config().max_dirty_pages, 64);
+        assert_eq!(engine.buffer_manager().flush_interval(), Some(Duration::from_millis(500)));
+        assert_eq!(engine.buffer_manager().pool_stats().unwrap().capacity, 250);
+        assert_eq!(throttle.budget_bytes_per_sec(), 1024 * 1024);
+
+        // Reapplying changes nothing; a zero interval stops background flushing
+        assert!(engine.apply_config_delta(&delta).unwrap().is_empty());
+        delta.set("flush_interval_ms", "0").unwrap();
+        assert_eq!(engine.apply_config_delta(&delta).unwrap(), vec![change("flush_interval_ms", 500, 0)]);
+        assert_eq!(engine.buffer_manager().flush_interval(), None);
+    }
+
+    #[test]
+    fn test_boot_only_and_invalid_changes_are_rejected() {
+        let dir = tempdir().unwrap();
+        let engine = engine(dir.path());
+
+        let mut delta = StorageConfigDelta::default();
+        assert!(matches!(delta.set("storage.wal_size", "1024"), Err(StorageError::RestartRequired { setting }) if setting == "wal_size"));
+        assert!(matches!(delta.set("storage.cache_mode", "1"), Err(StorageError::InvalidConfig { .. })));
+        assert!(matches!(delta.set("flush_interval_ms", "soon"), Err(StorageError::InvalidConfig { .. })));
+
+        delta.set("flush_interval_ms", "250").unwrap();
+        delta.set("storage.page_size", "8192").unwrap();
+        let error = engine.apply_config_delta(&delta).unwrap_err();
+        assert!(matches!(&error, StorageError::RestartRequired { setting } if setting == "page_size"));
+        assert!(error.to_string().contains("restart"));
+
+        // A rejected delta leaves every setting in it unchanged
+        delta.page_size = None;
+        delta.buffer_pool_size = Some(50);
+        assert!(matches!(engine.apply_config_delta(&delta), Err(StorageError::InvalidConfig { .. })));
+        assert_eq!(engine.config().flush_interval_ms, 1000);
+        assert_eq!(engine.buffer_manager().pool_stats().unwrap().capacity, 100);
+    }
+
+    #[test]
+    fn test_attached_compaction_sees_page_write_latency() {
+        struct NoFiles;
+        impl CompactionSource for NoFiles {
+            fn candidate_files(&self) -> std::io::Result<Vec<FileMetadata>> {
+                Ok(Vec::new())
+            }
+            fn compact(&self, _task: &CompactionTask, _io: &CompactionIo<'_>) -> std::io::Result<Vec<FileMetadata>> {
+                Ok(Vec::new())
+            }
+        }
+
+        let dir = tempdir().unwrap();
+        let engine = engine(dir.path());
+        let scheduler = CompactionScheduler::new(CompactionSchedulerConfig::default(), Arc::new(NoFiles));
+        engine.attach_compaction(&scheduler);
+        assert_eq!(scheduler.io_throttle().budget_bytes_per_sec(), engine.config().compaction_io_budget_bytes_per_sec);
+
+        for _ in 0..10 {
+            engine.buffer_manager().allocate_page(PageType::Data, VersionId(1)).unwrap();
+        }
+        engine.close().unwrap();
+        assert!(scheduler.latency_monitor().p99().is_some());
+    }
+
+    #[test]
+    fn test_encryption_is_enabled_in_place_and_finished_in_the_background() {
+        let dir = tempdir().unwrap();
+        let config = StorageConfig {
+            path: dir.path().join("engine.db"),
+            buffer_pool_size: 100,
+            ..StorageConfig::default()
+        };
+        {
+            let engine = engine(dir.path());
+            for _ in 0..10 {
+                engine.buffer_manager().allocate_page(PageType::Data, VersionId(1)).unwrap();
+            }
+        }
+
+        let engine = StorageEngine::open_encrypted(config.clone(), Arc::new(MasterKey::from_bytes([1; 32]))).unwrap();
+        assert!(!engine.encryption_status().is_encrypted());
+        assert_eq!(engine.enable_encryption().unwrap(), 1);
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        while engine.encryption_status().rotation_percent().is_some() {
+            assert!(std::time::Instant::now() < deadline, "re-encryption did not finish");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(engine.encryption_status().encryption.unwrap().keys.len(), 1);
+        drop(engine);
+
+        assert!(matches!(StorageEngine::open(config), Err(StorageError::EncryptionKeyRequired { .. })));
+    }
+}
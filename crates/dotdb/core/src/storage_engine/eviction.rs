@@ -0,0 +1,459 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+// Buffer pool eviction policies
+// This module defines the EvictionPolicy trait used by the buffer pool to decide which page to
+// evict, along with LRU, LRU-K, Clock, MRU and FIFO implementations.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::storage_engine::file_format::PageId;
+
+/// Replacement policy selector
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReplacementPolicy {
+    /// Least recently used
+    #[default]
+    LRU,
+    /// LRU-K: evict the page with the largest backward K-distance
+    LRUK(usize),
+    /// Clock-sweep (approximated LRU)
+    Clock,
+    /// Most recently used
+    MRU,
+    /// First in, first out
+    FIFO,
+}
+
+impl ReplacementPolicy {
+    /// LRU-K with K=2, the usual choice for scan resistance
+    pub fn lru_2() -> Self {
+        ReplacementPolicy::LRUK(2)
+    }
+
+    /// Build the policy implementation for this selector
+    pub fn build(&self, capacity: usize) -> Box<dyn EvictionPolicy> {
+        match *self {
+            ReplacementPolicy::LRU => Box::new(LruPolicy::new(capacity)),
+            ReplacementPolicy::LRUK(k) => Box::new(LruKPolicy::new(k)),
+            ReplacementPolicy::Clock => Box::new(ClockPolicy::new(capacity)),
+            ReplacementPolicy::MRU => Box::new(MruPolicy::new(capacity)),
+            ReplacementPolicy::FIFO => Box::new(FifoPolicy::new(capacity)),
+        }
+    }
+}
+
+/// How a page is expected to be used by the caller
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AccessHint {
+    /// Regular access, the page may be reused
+    #[default]
+    Normal,
+    /// The page is read once (e.g. by a sequential scan) and should not displace the hot set
+    UseOnce,
+}
+
+/// Strategy deciding which resident page the buffer pool evicts next
+///
+/// The buffer pool reports every insert, access and removal; the policy only tracks page ids
+/// and never owns page data. Pinned pages are filtered through the `evictable` predicate.
+pub trait EvictionPolicy: Send + Sync {
+    /// Short name of the policy, used in statistics
+    fn name(&self) -> &'static str;
+
+    /// Record an access to a resident page
+    fn record_access(&mut self, page_id: PageId, hint: AccessHint);
+
+    /// Record a page that was just brought into the pool
+    fn record_insert(&mut self, page_id: PageId, hint: AccessHint);
+
+    /// Forget a page that left the pool
+    fn record_remove(&mut self, page_id: PageId);
+
+    /// Choose the next page to evict among those for which `evictable` returns true
+    fn pick_victim(&mut self, evictable: &dyn Fn(PageId) -> bool) -> Option<PageId>;
+
+    /// Drop all tracking state
+    fn clear(&mut self);
+}
+
+/// Least recently used: front of the queue is the coldest page
+#[derive(Debug, Default)]
+pub struct LruPolicy {
+    queue: VecDeque<PageId>,
+}
+
+impl LruPolicy {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            queue: VecDeque::with_capacity(capacity),
+        }
+    }
+}
+
+impl EvictionPolicy for LruPolicy {
+    fn name(&self) -> &'static str {
+        "lru"
+    }
+
+    fn record_access(&mut self, page_id: PageId, hint: AccessHint) {
+        // Use-once accesses must not promote the page into the hot end
+        if hint == AccessHint::UseOnce {
+            return;
+        }
+        self.queue.retain(|&id| id != page_id);
+        self.queue.push_back(page_id);
+    }
+
+    fn record_insert(&mut self, page_id: PageId, hint: AccessHint) {
+        match hint {
+            AccessHint::Normal => self.queue.push_back(page_id),
+            AccessHint::UseOnce => self.queue.push_front(page_id),
+        }
+    }
+
+    fn record_remove(&mut self, page_id: PageId) {
+        self.queue.retain(|&id| id != page_id);
+    }
+
+    fn pick_victim(&mut self, evictable: &dyn Fn(PageId) -> bool) -> Option<PageId> {
+        self.queue.iter().copied().find(|&id| evictable(id))
+    }
+
+    fn clear(&mut self) {
+        self.queue.clear();
+    }
+}
+
+/// Most recently used: front of the queue is the most recent page
+#[derive(Debug, Default)]
+pub struct MruPolicy {
+    queue: VecDeque<PageId>,
+}
+
+impl MruPolicy {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            queue: VecDeque::with_capacity(capacity),
+        }
+    }
+}
+
+impl EvictionPolicy for MruPolicy {
+    fn name(&self) -> &'static str {
+        "mru"
+    }
+
+    fn record_access(&mut self, page_id: PageId, _hint: AccessHint) {
+        self.queue.retain(|&id| id != page_id);
+        self.queue.push_front(page_id);
+    }
+
+    fn record_insert(&mut self, page_id: PageId, _hint: AccessHint) {
+        self.queue.push_front(page_id);
+    }
+
+    fn record_remove(&mut self, page_id: PageId) {
+        self.queue.retain(|&id| id != page_id);
+    }
+
+    fn pick_victim(&mut self, evictable: &dyn Fn(PageId) -> bool) -> Option<PageId> {
+        self.queue.iter().copied().find(|&id| evictable(id))
+    }
+
+    fn clear(&mut self) {
+        self.queue.clear();
+    }
+}
+
+/// First in, first out: accesses do not change the order
+#[derive(Debug, Default)]
+pub struct FifoPolicy {
+    queue: VecDeque<PageId>,
+}
+
+impl FifoPolicy {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            queue: VecDeque::with_capacity(capacity),
+        }
+    }
+}
+
+impl EvictionPolicy for FifoPolicy {
+    fn name(&self) -> &'static str {
+        "fifo"
+    }
+
+    fn record_access(&mut self, _page_id: PageId, _hint: AccessHint) {}
+
+    fn record_insert(&mut self, page_id: PageId, hint: AccessHint) {
+        match hint {
+            AccessHint::Normal => self.queue.push_back(page_id),
+            AccessHint::UseOnce => self.queue.push_front(page_id),
+        }
+    }
+
+    fn record_remove(&mut self, page_id: PageId) {
+        self.queue.retain(|&id| id != page_id);
+    }
+
+    fn pick_victim(&mut self, evictable: &dyn Fn(PageId) -> bool) -> Option<PageId> {
+        self.queue.iter().copied().find(|&id| evictable(id))
+    }
+
+    fn clear(&mut self) {
+        self.queue.clear();
+    }
+}
+
+/// Clock-sweep: pages get a second chance while their reference bit is set
+#[derive(Debug, Default)]
+pub struct ClockPolicy {
+    /// Ring of resident pages with their reference bit
+    ring: Vec<(PageId, bool)>,
+    /// Current hand position
+    hand: usize,
+}
+
+impl ClockPolicy {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            ring: Vec::with_capacity(capacity),
+            hand: 0,
+        }
+    }
+
+    fn position(&self, page_id: PageId) -> Option<usize> {
+        self.ring.iter().position(|(id, _)| *id == page_id)
+    }
+}
+
+impl EvictionPolicy for ClockPolicy {
+    fn name(&self) -> &'static str {
+        "clock"
+    }
+
+    fn record_access(&mut self, page_id: PageId, hint: AccessHint) {
+        if hint == AccessHint::UseOnce {
+            return;
+        }
+        if let Some(pos) = self.position(page_id) {
+            self.ring[pos].1 = true;
+        }
+    }
+
+    fn record_insert(&mut self, page_id: PageId, _hint: AccessHint) {
+        // New pages start with a clear reference bit, so a page must be re-referenced to survive a sweep
+        self.ring.push((page_id, false));
+    }
+
+    fn record_remove(&mut self, page_id: PageId) {
+        if let Some(pos) = self.position(page_id) {
+            self.ring.remove(pos);
+            if pos < self.hand {
+                self.hand -= 1;
+            }
+            if self.hand >= self.ring.len() {
+                self.hand = 0;
+            }
+        }
+    }
+
+    fn pick_victim(&mut self, evictable: &dyn Fn(PageId) -> bool) -> Option<PageId> {
+        if self.ring.is_empty() {
+            return None;
+        }
+
+        // Two full sweeps: the first clears reference bits, the second is guaranteed to find
+        // an unreferenced page unless everything is pinned
+        let len = self.ring.len();
+        for _ in 0..(2 * len) {
+            let pos = self.hand % len;
+            let (page_id, referenced) = self.ring[pos];
+            self.hand = (pos + 1) % len;
+
+            if !evictable(page_id) {
+                continue;
+            }
+            if referenced {
+                self.ring[pos].1 = false;
+                continue;
+            }
+            return Some(page_id);
+        }
+
+        None
+    }
+
+    fn clear(&mut self) {
+        self.ring.clear();
+        self.hand = 0;
+    }
+}
+
+/// LRU-K: evict the page whose K-th most recent reference is oldest
+///
+/// Pages with fewer than K references have an infinite backward K-distance and are evicted
+/// first, oldest last reference first. This keeps pages touched once by a large scan from
+/// pushing out pages that are referenced repeatedly.
+#[derive(Debug)]
+pub struct LruKPolicy {
+    /// K (number of references tracked per page)
+    k: usize,
+    /// Logical clock advanced on every reference
+    clock: u64,
+    /// Most recent reference times per page, newest last
+    history: HashMap<PageId, VecDeque<u64>>,
+}
+
+impl LruKPolicy {
+    pub fn new(k: usize) -> Self {
+        Self {
+            k: k.max(1),
+            clock: 0,
+            history: HashMap::new(),
+        }
+    }
+
+    fn touch(&mut self, page_id: PageId) {
+        self.clock += 1;
+        let now = self.clock;
+        let k = self.k;
+        let entry = self.history.entry(page_id).or_default();
+        entry.push_back(now);
+        while entry.len() > k {
+            entry.pop_front();
+        }
+    }
+}
+
+impl EvictionPolicy for LruKPolicy {
+    fn name(&self) -> &'static str {
+        "lru-k"
+    }
+
+    fn record_access(&mut self, page_id: PageId, hint: AccessHint) {
+        if hint == AccessHint::UseOnce {
+            return;
+        }
+        self.touch(page_id);
+    }
+
+    fn record_insert(&mut self, page_id: PageId, hint: AccessHint) {
+        match hint {
+            AccessHint::Normal => self.touch(page_id),
+            // No history at all makes the page the first candidate for eviction
+            AccessHint::UseOnce => {
+                self.history.insert(page_id, VecDeque::new());
+            }
+        }
+    }
+
+    fn record_remove(&mut self, page_id: PageId) {
+        self.history.remove(&page_id);
+    }
+
+    fn pick_victim(&mut self, evictable: &dyn Fn(PageId) -> bool) -> Option<PageId> {
+        let k = self.k;
+        // Sort key: pages with fewer than K references come first (false < true),
+        // then by the reference time that defines their distance
+        self.history
+            .iter()
+            .filter(|(id, _)| evictable(**id))
+            .min_by_key(|(id, refs)| {
+                let full = refs.len() >= k;
+                let time = if full { refs.front().copied().unwrap_or(0) } else { refs.back().copied().unwrap_or(0) };
+                (full, time, id.0)
+            })
+            .map(|(id, _)| *id)
+    }
+
+    fn clear(&mut self) {
+        self.history.clear();
+        self.clock = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all(_: PageId) -> bool {
+        true
+    }
+
+    #[test]
+    fn test_lru_use_once_is_evicted_first() {
+        let mut policy = LruPolicy::new(4);
+        policy.record_insert(PageId(1), AccessHint::Normal);
+        policy.record_insert(PageId(2), AccessHint::Normal);
+        policy.record_insert(PageId(3), AccessHint::UseOnce);
+
+        assert_eq!(policy.pick_victim(&all), Some(PageId(3)));
+    }
+
+    #[test]
+    fn test_lru_k_prefers_pages_with_short_history() {
+        let mut policy = LruKPolicy::new(2);
+        policy.record_insert(PageId(1), AccessHint::Normal);
+        policy.record_access(PageId(1), AccessHint::Normal);
+        policy.record_insert(PageId(2), AccessHint::Normal);
+
+        // Page 2 has only one reference, so its backward 2-distance is infinite
+        assert_eq!(policy.pick_victim(&all), Some(PageId(2)));
+
+        policy.record_access(PageId(2), AccessHint::Normal);
+        // Both have two references now; page 1's second most recent reference is older
+        assert_eq!(policy.pick_victim(&all), Some(PageId(1)));
+    }
+
+    #[test]
+    fn test_lru_k_scan_does_not_displace_hot_set() {
+        let mut policy = LruKPolicy::new(2);
+        for id in 0..3 {
+            policy.record_insert(PageId(id), AccessHint::Normal);
+            policy.record_access(PageId(id), AccessHint::Normal);
+        }
+        for id in 100..110 {
+            policy.record_insert(PageId(id), AccessHint::UseOnce);
+            let victim = policy.pick_victim(&all).unwrap();
+            assert!(victim.0 >= 100);
+            policy.record_remove(victim);
+        }
+    }
+
+    #[test]
+    fn test_clock_second_chance() {
+        let mut policy = ClockPolicy::new(3);
+        for id in 0..3 {
+            policy.record_insert(PageId(id), AccessHint::Normal);
+        }
+        policy.record_access(PageId(0), AccessHint::Normal);
+
+        assert_eq!(policy.pick_victim(&all), Some(PageId(1)));
+    }
+
+    #[test]
+    fn test_clock_skips_pinned_pages() {
+        let mut policy = ClockPolicy::new(2);
+        policy.record_insert(PageId(0), AccessHint::Normal);
+        policy.record_insert(PageId(1), AccessHint::Normal);
+
+        assert_eq!(policy.pick_victim(&|id: PageId| id != PageId(0)), Some(PageId(1)));
+        assert_eq!(policy.pick_victim(&|_| false), None);
+    }
+}
@@ -0,0 +1,332 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+// Failpoint module
+// The WAL and the storage file route their writes and fsyncs through the hooks in this module. Without the `failpoints` feature the hooks are plain `write_all` and `sync_all` calls. With it, a `FaultInjector` installed for a directory consults its `FailpointPolicy` at every named failpoint under that directory and can tear a write, drop an fsync or crash the storage, and can later revert every write no completed fsync covers, as a power loss would.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+#[cfg(feature = "failpoints")]
+use std::collections::HashMap;
+#[cfg(feature = "failpoints")]
+use std::fmt;
+#[cfg(feature = "failpoints")]
+use std::fs::OpenOptions;
+#[cfg(feature = "failpoints")]
+use std::io::{Read, Seek, SeekFrom};
+#[cfg(feature = "failpoints")]
+use std::path::Path;
+#[cfg(feature = "failpoints")]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "failpoints")]
+use std::sync::{Arc, Mutex, Weak};
+
+/// A place in the storage engine where faults can be injected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Failpoint {
+    /// Appending a record to a WAL segment
+    WalAppend,
+    /// Syncing the WAL
+    WalSync,
+    /// Writing a page to the storage file
+    PageWrite,
+    /// Writing the storage file header
+    HeaderWrite,
+    /// Syncing the storage file
+    DataSync,
+}
+
+#[cfg(feature = "failpoints")]
+impl Failpoint {
+    /// Every failpoint
+    pub const ALL: [Failpoint; 5] = [Failpoint::WalAppend, Failpoint::WalSync, Failpoint::PageWrite, Failpoint::HeaderWrite, Failpoint::DataSync];
+
+    /// Name of the failpoint, e.g. `wal.append`
+    pub fn name(self) -> &'static str {
+        match self {
+            Failpoint::WalAppend => "wal.append",
+            Failpoint::WalSync => "wal.sync",
+            Failpoint::PageWrite => "page.write",
+            Failpoint::HeaderWrite => "header.write",
+            Failpoint::DataSync => "data.sync",
+        }
+    }
+
+    /// Whether the failpoint is an fsync rather than a write
+    pub fn is_sync(self) -> bool {
+        matches!(self, Failpoint::WalSync | Failpoint::DataSync)
+    }
+}
+
+#[cfg(feature = "failpoints")]
+impl fmt::Display for Failpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Write `buf` at the file's current position
+#[cfg(not(feature = "failpoints"))]
+#[inline(always)]
+pub(crate) fn write_all(_failpoint: Failpoint, file: &mut File, _path: impl FnOnce() -> PathBuf, buf: &[u8]) -> io::Result<()> {
+    file.write_all(buf)
+}
+
+/// Sync the file to disk
+#[cfg(not(feature = "failpoints"))]
+#[inline(always)]
+pub(crate) fn sync_all(_failpoint: Failpoint, file: &File, _path: impl FnOnce() -> PathBuf) -> io::Result<()> {
+    file.sync_all()
+}
+
+/// Write `buf` at the file's current position, unless the fault injector for `path` says otherwise
+#[cfg(feature = "failpoints")]
+pub(crate) fn write_all(failpoint: Failpoint, file: &mut File, path: impl FnOnce() -> PathBuf, buf: &[u8]) -> io::Result<()> {
+    let path = path();
+    match injector_for(&path) {
+        Some(injector) => injector.write(failpoint, file, &path, buf),
+        None => file.write_all(buf),
+    }
+}
+
+/// Sync the file to disk, unless the fault injector for `path` says otherwise
+///
+/// `path` names the synced file, or a directory every file of which is durable once the sync completes.
+#[cfg(feature = "failpoints")]
+pub(crate) fn sync_all(failpoint: Failpoint, file: &File, path: impl FnOnce() -> PathBuf) -> io::Result<()> {
+    let path = path();
+    match injector_for(&path) {
+        Some(injector) => injector.sync(failpoint, file, &path),
+        None => file.sync_all(),
+    }
+}
+
+/// What happens when a failpoint is reached
+#[cfg(feature = "failpoints")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailpointAction {
+    /// Perform the write or sync
+    Continue,
+    /// Persist only the first `persisted` bytes of the write, rounded down to a sector
+    /// boundary, then crash
+    TornWrite { persisted: usize },
+    /// Report the sync as done without syncing; writes it should have covered are lost on
+    /// [`power_loss`](FaultInjector::power_loss)
+    DropSync,
+    /// Abort before the write or sync: it and every later write or sync under the
+    /// injector's directory fail
+    Crash,
+}
+
+/// Decides what happens at each failpoint reached under a [`FaultInjector`]'s directory
+///
+/// Closures taking the failpoint, the path written or synced and the write length (0 for
+/// syncs) are policies too.
+#[cfg(feature = "failpoints")]
+pub trait FailpointPolicy: Send + Sync {
+    fn evaluate(&self, failpoint: Failpoint, path: &Path, len: usize) -> FailpointAction;
+}
+
+#[cfg(feature = "failpoints")]
+impl<F> FailpointPolicy for F
+where
+    F: Fn(Failpoint, &Path, usize) -> FailpointAction + Send + Sync,
+{
+    fn evaluate(&self, failpoint: Failpoint, path: &Path, len: usize) -> FailpointAction {
+        self(failpoint, path, len)
+    }
+}
+
+/// Takes `action` the `hit`th time `failpoint` is reached and continues otherwise
+#[cfg(feature = "failpoints")]
+#[derive(Debug)]
+pub struct TriggerAt {
+    failpoint: Failpoint,
+    hit: u64,
+    action: FailpointAction,
+    seen: AtomicU64,
+}
+
+#[cfg(feature = "failpoints")]
+impl TriggerAt {
+    /// Trigger `action` on the `hit`th visit to `failpoint`, counting from 1
+    pub fn new(failpoint: Failpoint, hit: u64, action: FailpointAction) -> Self {
+        Self {
+            failpoint,
+            hit,
+            action,
+            seen: AtomicU64::new(0),
+        }
+    }
+}
+
+#[cfg(feature = "failpoints")]
+impl FailpointPolicy for TriggerAt {
+    fn evaluate(&self, failpoint: Failpoint, _path: &Path, _len: usize) -> FailpointAction {
+        if failpoint == self.failpoint && self.seen.fetch_add(1, Ordering::SeqCst) + 1 == self.hit {
+            self.action
+        } else {
+            FailpointAction::Continue
+        }
+    }
+}
+
+/// Writes are torn at sector granularity, as disks only guarantee that whole sectors persist
+#[cfg(feature = "failpoints")]
+const SECTOR_SIZE: u64 = 512;
+
+/// Bytes a write replaced, so it can be reverted if no sync covers it
+#[cfg(feature = "failpoints")]
+struct UndoRecord {
+    offset: u64,
+    previous: Vec<u8>,
+    previous_len: u64,
+}
+
+#[cfg(feature = "failpoints")]
+#[derive(Default)]
+struct InjectorState {
+    /// The failpoint the storage crashed at
+    crashed: Option<Failpoint>,
+    /// Writes not yet covered by a sync, oldest first, per file
+    unsynced: HashMap<PathBuf, Vec<UndoRecord>>,
+    hits: HashMap<Failpoint, u64>,
+}
+
+#[cfg(feature = "failpoints")]
+struct Injector {
+    root: PathBuf,
+    policy: Box<dyn FailpointPolicy>,
+    state: Mutex<InjectorState>,
+}
+
+/// Injectors by directory; entries whose handle has been dropped are pruned on install
+#[cfg(feature = "failpoints")]
+static INJECTORS: Mutex<Vec<Weak<Injector>>> = Mutex::new(Vec::new());
+
+#[cfg(feature = "failpoints")]
+fn injector_for(path: &Path) -> Option<Arc<Injector>> {
+    INJECTORS.lock().unwrap().iter().filter_map(Weak::upgrade).find(|injector| path.starts_with(&injector.root))
+}
+
+#[cfg(feature = "failpoints")]
+fn crash_error(failpoint: Failpoint) -> io::Error {
+    io::Error::other(format!("simulated crash at failpoint {failpoint}"))
+}
+
+#[cfg(feature = "failpoints")]
+impl Injector {
+    fn evaluate(&self, state: &mut InjectorState, failpoint: Failpoint, path: &Path, len: usize) -> io::Result<FailpointAction> {
+        if let Some(crashed) = state.crashed {
+            return Err(crash_error(crashed));
+        }
+        *state.hits.entry(failpoint).or_default() += 1;
+        Ok(self.policy.evaluate(failpoint, path, len))
+    }
+
+    fn write(&self, failpoint: Failpoint, file: &mut File, path: &Path, buf: &[u8]) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let action = self.evaluate(&mut state, failpoint, path, buf.len())?;
+        if action == FailpointAction::Crash {
+            state.crashed = Some(failpoint);
+            return Err(crash_error(failpoint));
+        }
+
+        // Keep what the write replaces until a sync makes it durable
+        let offset = file.stream_position()?;
+        let previous_len = file.metadata()?.len();
+        let mut previous = vec![0; (buf.len() as u64).min(previous_len.saturating_sub(offset)) as usize];
+        file.read_exact(&mut previous)?;
+        file.seek(SeekFrom::Start(offset))?;
+        state.unsynced.entry(path.to_path_buf()).or_default().push(UndoRecord { offset, previous, previous_len });
+
+        match action {
+            FailpointAction::TornWrite { persisted } => {
+                let end = (offset + persisted.min(buf.len()) as u64) / SECTOR_SIZE * SECTOR_SIZE;
+                file.write_all(&buf[..end.saturating_sub(offset) as usize])?;
+                state.crashed = Some(failpoint);
+                Err(crash_error(failpoint))
+            }
+            _ => file.write_all(buf),
+        }
+    }
+
+    fn sync(&self, failpoint: Failpoint, file: &File, path: &Path) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        match self.evaluate(&mut state, failpoint, path, 0)? {
+            FailpointAction::Crash => {
+                state.crashed = Some(failpoint);
+                Err(crash_error(failpoint))
+            }
+            FailpointAction::DropSync => Ok(()),
+            FailpointAction::Continue | FailpointAction::TornWrite { .. } => {
+                file.sync_all()?;
+                state.unsynced.retain(|written, _| !written.starts_with(path));
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Injects faults into the storage engine files under a directory until dropped
+#[cfg(feature = "failpoints")]
+pub struct FaultInjector {
+    injector: Arc<Injector>,
+}
+
+#[cfg(feature = "failpoints")]
+impl FaultInjector {
+    /// Consult `policy` at every failpoint reached for files under `root`
+    pub fn install(root: impl Into<PathBuf>, policy: impl FailpointPolicy + 'static) -> Self {
+        let injector = Arc::new(Injector {
+            root: root.into(),
+            policy: Box::new(policy),
+            state: Mutex::new(InjectorState::default()),
+        });
+        let mut injectors = INJECTORS.lock().unwrap();
+        injectors.retain(|installed| installed.strong_count() > 0);
+        injectors.push(Arc::downgrade(&injector));
+        Self { injector }
+    }
+
+    /// The failpoint the storage crashed at, if it has
+    pub fn crashed(&self) -> Option<Failpoint> {
+        self.injector.state.lock().unwrap().crashed
+    }
+
+    /// How many times `failpoint` has been reached
+    pub fn hits(&self, failpoint: Failpoint) -> u64 {
+        self.injector.state.lock().unwrap().hits.get(&failpoint).copied().unwrap_or(0)
+    }
+
+    /// Revert every write that no completed sync covers, newest first, as if the machine
+    /// lost power. Storage using these files must be closed first.
+    pub fn power_loss(&self) -> io::Result<()> {
+        let mut state = self.injector.state.lock().unwrap();
+        for (path, records) in state.unsynced.drain() {
+            let mut file = OpenOptions::new().write(true).open(&path)?;
+            for record in records.iter().rev() {
+                file.seek(SeekFrom::Start(record.offset))?;
+                file.write_all(&record.previous)?;
+                file.set_len(record.previous_len)?;
+            }
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+}
@@ -17,19 +17,41 @@
 // File format module
 // This module defines the on-disk format for persistent storage, including page layout, headers, and file structure. It provides methods for reading, writing, allocating, and freeing pages, as well as managing file metadata and versions.
 
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::fs::{File, OpenOptions};
 use std::io::{self, Read, Seek, SeekFrom, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
 
-use crate::storage_engine::lib::{StorageConfig, StorageError, StorageResult, VersionId};
+use tracing::{info, warn};
+
+use crate::compaction::scheduler::WriteLatencyMonitor;
+use crate::io::aligned::{AlignedBuffer, logical_block_size};
+use crate::io::page_io::{check_direct_page_size, open_page_file};
+use crate::storage_engine::encryption::{DataKey, EncryptionHeader, EncryptionStatus, KeyProvider, Keyring, NONCE_SIZE, ReencryptionBatch, RotationProgress, TAG_SIZE, WrappedKey};
+use crate::storage_engine::failpoint::{self, Failpoint};
+use crate::storage_engine::lib::{StorageConfig, StorageError, StorageResult, VersionId, generate_timestamp};
 
 /// Magic number to identify our file format (DOTDB)
 const FILE_MAGIC: [u8; 4] = [0x44, 0x4F, 0x54, 0x44];
 /// Current format version
 const FORMAT_VERSION: u32 = 1;
+/// Format version of encrypted files, which older builds refuse to open
+const ENCRYPTED_FORMAT_VERSION: u32 = 2;
+/// Offset of the encryption section in the file header
+const ENCRYPTION_OFFSET: usize = 64;
+/// Offset of the wrapped keys in the file header
+const KEYRING_OFFSET: usize = 96;
+/// Offset of the encryption seal, a nonce followed by a key ID, in the reserved page header bytes
+const SEAL_OFFSET: usize = 19;
 /// Size of the file header in bytes
 const HEADER_SIZE: usize = 4096;
+/// Extension of the corruption log written next to the storage file
+pub const CORRUPTION_LOG_EXTENSION: &str = "corruption.log";
+/// Suffix of the file listing the quarantined pages, written next to the storage file
+pub const QUARANTINE_SUFFIX: &str = "quarantine";
 
 /// Unique identifier for a page within the storage file
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -58,6 +80,15 @@ pub enum PageType {
     Data = 2,
     /// Free (unused) page
     Free = 3,
+    /// Secondary index node, rebuildable from primary data
+    Index = 4,
+}
+
+impl PageType {
+    /// Whether the page holds derived index data rather than primary data
+    pub fn is_index(&self) -> bool {
+        matches!(self, PageType::Index)
+    }
 }
 
 impl From<u8> for PageType {
@@ -67,6 +98,7 @@ impl From<u8> for PageType {
             1 => PageType::Node,
             2 => PageType::Data,
             3 => PageType::Free,
+            4 => PageType::Index,
             _ => PageType::Free, // Default to Free for unknown types
         }
     }
@@ -230,6 +262,8 @@ struct FileHeader {
     current_version: VersionId,
     /// ID of the first free page
     first_free_page: PageId,
+    /// Keyring and re-encryption progress of an encrypted file
+    encryption: Option<EncryptionHeader>,
 }
 
 impl FileHeader {
@@ -242,9 +276,15 @@ impl FileHeader {
             total_pages: 1, // At minimum, we have the header page
             current_version: VersionId(0),
             first_free_page: PageId(0),
+            encryption: None,
         }
     }
 
+    /// Version written to disk: encrypted files need a build that understands encryption
+    fn format_version(&self) -> u32 {
+        if self.encryption.is_some() { ENCRYPTED_FORMAT_VERSION } else { self.version }
+    }
+
     /// Serialize the header to bytes
     fn serialize(&self, buffer: &mut [u8]) -> StorageResult<()> {
         if buffer.len() < HEADER_SIZE {
@@ -258,7 +298,7 @@ impl FileHeader {
         buffer[0..4].copy_from_slice(&self.magic);
 
         // Format version
-        buffer[4..8].copy_from_slice(&self.version.to_le_bytes());
+        buffer[4..8].copy_from_slice(&self.format_version().to_le_bytes());
 
         // Page size
         buffer[8..12].copy_from_slice(&self.page_size.to_le_bytes());
@@ -272,9 +312,77 @@ impl FileHeader {
         // First free page
         buffer[28..36].copy_from_slice(&self.first_free_page.0.to_le_bytes());
 
+        if let Some(encryption) = &self.encryption {
+            Self::serialize_encryption(encryption, buffer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Serialize the encryption section: flags, active key and re-encryption progress,
+    /// followed by each key's ID, creation time, wrapped length and wrapped bytes
+    fn serialize_encryption(encryption: &EncryptionHeader, buffer: &mut [u8]) -> StorageResult<()> {
+        let section = &mut buffer[ENCRYPTION_OFFSET..];
+        section[0] = 1;
+        section[1] = encryption.active_key;
+        section[2] = encryption.keys.len() as u8;
+        if let Some(rotation) = &encryption.rotation {
+            section[3] = 1;
+            section[8..16].copy_from_slice(&rotation.next_page.to_le_bytes());
+            section[16..24].copy_from_slice(&rotation.pages_rewritten.to_le_bytes());
+            section[24..32].copy_from_slice(&rotation.pages_skipped.to_le_bytes());
+        }
+
+        let mut offset = KEYRING_OFFSET;
+        for key in &encryption.keys {
+            let end = offset + 11 + key.wrapped.len();
+            if end > HEADER_SIZE {
+                return Err(StorageError::Encryption(format!("{} encryption keys do not fit in the file header", encryption.keys.len())));
+            }
+            buffer[offset] = key.id;
+            buffer[offset + 1..offset + 9].copy_from_slice(&key.created_at.to_le_bytes());
+            buffer[offset + 9..offset + 11].copy_from_slice(&(key.wrapped.len() as u16).to_le_bytes());
+            buffer[offset + 11..end].copy_from_slice(&key.wrapped);
+            offset = end;
+        }
         Ok(())
     }
 
+    /// Deserialize the encryption section, `None` if the file is not encrypted
+    fn deserialize_encryption(buffer: &[u8]) -> StorageResult<Option<EncryptionHeader>> {
+        let section = &buffer[ENCRYPTION_OFFSET..KEYRING_OFFSET];
+        if section[0] == 0 {
+            return Ok(None);
+        }
+        let read_u64 = |bytes: &[u8]| u64::from_le_bytes(bytes.try_into().expect("eight bytes"));
+        let rotation = (section[3] == 1).then(|| RotationProgress {
+            next_page: read_u64(&section[8..16]),
+            pages_rewritten: read_u64(&section[16..24]),
+            pages_skipped: read_u64(&section[24..32]),
+        });
+
+        let mut keys = Vec::new();
+        let mut offset = KEYRING_OFFSET;
+        for _ in 0..section[2] {
+            let invalid = || StorageError::Corruption("Encryption keyring extends past the file header".to_string());
+            let fixed = buffer.get(offset..offset + 11).ok_or_else(invalid)?;
+            let wrapped_len = u16::from_le_bytes([fixed[9], fixed[10]]) as usize;
+            let wrapped = buffer.get(offset + 11..offset + 11 + wrapped_len).ok_or_else(invalid)?;
+            keys.push(WrappedKey {
+                id: fixed[0],
+                created_at: read_u64(&fixed[1..9]),
+                wrapped: wrapped.to_vec(),
+            });
+            offset += 11 + wrapped_len;
+        }
+
+        Ok(Some(EncryptionHeader {
+            active_key: section[1],
+            keys,
+            rotation,
+        }))
+    }
+
     /// Deserialize the header from bytes
     fn deserialize(buffer: &[u8]) -> StorageResult<Self> {
         if buffer.len() < HEADER_SIZE {
@@ -292,7 +400,7 @@ impl FileHeader {
         let version = u32::from_le_bytes(buffer[4..8].try_into().map_err(|_| StorageError::Corruption("Invalid version bytes".to_string()))?);
 
         // Check version compatibility
-        if version > FORMAT_VERSION {
+        if version > ENCRYPTED_FORMAT_VERSION {
             return Err(StorageError::Corruption(format!("Unsupported format version: {version}")));
         }
 
@@ -312,6 +420,9 @@ impl FileHeader {
             buffer[28..36].try_into().map_err(|_| StorageError::Corruption("Invalid first_free_page bytes".to_string()))?,
         ));
 
+        // Encryption section
+        let encryption = if version >= ENCRYPTED_FORMAT_VERSION { Self::deserialize_encryption(buffer)? } else { None };
+
         Ok(Self {
             magic,
             version,
@@ -319,11 +430,153 @@ impl FileHeader {
             total_pages,
             current_version,
             first_free_page,
+            encryption,
+        })
+    }
+}
+
+/// A page whose stored checksum does not match its content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorruptPage {
+    pub page_id: PageId,
+    /// Page type as recorded in the (possibly damaged) header
+    pub page_type: PageType,
+    pub stored_checksum: u32,
+    pub computed_checksum: u32,
+}
+
+impl CorruptPage {
+    /// Human-readable description, pointing at `rebuild` when the page only holds index data
+    pub fn describe(&self) -> String {
+        if self.page_type.is_index() {
+            format!(
+                "Index page {} has invalid checksum (stored {:08x}, computed {:08x}); index data is recoverable, run rebuild on the affected index",
+                self.page_id.0, self.stored_checksum, self.computed_checksum
+            )
+        } else {
+            format!(
+                "Page {} has invalid checksum (stored {:08x}, computed {:08x})",
+                self.page_id.0, self.stored_checksum, self.computed_checksum
+            )
+        }
+    }
+
+    /// Line recording the page in the quarantine file
+    fn quarantine_entry(&self) -> String {
+        format!("{} {} {:08x} {:08x}", self.page_id.0, self.page_type as u8, self.stored_checksum, self.computed_checksum)
+    }
+
+    fn parse_quarantine_entry(line: &str) -> Option<Self> {
+        let mut fields = line.split_whitespace();
+        let page = Self {
+            page_id: PageId(fields.next()?.parse().ok()?),
+            page_type: PageType::from(fields.next()?.parse::<u8>().ok()?),
+            stored_checksum: u32::from_str_radix(fields.next()?, 16).ok()?,
+            computed_checksum: u32::from_str_radix(fields.next()?, 16).ok()?,
+        };
+        fields.next().is_none().then_some(page)
+    }
+}
+
+/// Summary of a full scan of the storage file
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    /// Number of pages checked, excluding the file header
+    pub pages_scanned: u64,
+    /// Pages on the free list (still checksummed, but hold no data)
+    pub free_pages: u64,
+    /// Pages whose checksum did not match
+    pub corrupt_pages: Vec<CorruptPage>,
+}
+
+impl VerifyReport {
+    pub fn is_clean(&self) -> bool {
+        self.corrupt_pages.is_empty()
+    }
+
+    /// Corrupt pages that can be recovered by rebuilding an index
+    pub fn corrupt_index_pages(&self) -> impl Iterator<Item = &CorruptPage> {
+        self.corrupt_pages.iter().filter(|page| page.page_type.is_index())
+    }
+}
+
+/// Key and nonce an encrypted page was sealed with
+#[derive(Debug, Clone, Copy)]
+struct PageSeal {
+    key_id: u8,
+    nonce: [u8; NONCE_SIZE],
+}
+
+impl PageSeal {
+    /// Read the seal from a serialized page header, `None` for a plaintext page
+    fn read(header: &[u8]) -> Option<Self> {
+        let key_id = header[SEAL_OFFSET + NONCE_SIZE];
+        (key_id != 0).then(|| Self {
+            key_id,
+            nonce: header[SEAL_OFFSET..SEAL_OFFSET + NONCE_SIZE].try_into().expect("nonce bytes"),
+        })
+    }
+
+    fn write(&self, header: &mut [u8]) {
+        header[SEAL_OFFSET..SEAL_OFFSET + NONCE_SIZE].copy_from_slice(&self.nonce);
+        header[SEAL_OFFSET + NONCE_SIZE] = self.key_id;
+    }
+
+    /// Data authenticated along with an encrypted page, binding it to its position and header
+    fn aad(page_id: PageId, header: &PageHeader, key_id: u8) -> [u8; 24] {
+        let mut aad = [0; 24];
+        aad[0..8].copy_from_slice(&page_id.0.to_le_bytes());
+        aad[8] = header.page_type as u8;
+        aad[9..17].copy_from_slice(&header.version.0.to_le_bytes());
+        aad[17..21].copy_from_slice(&header.ref_count.to_le_bytes());
+        aad[21..23].copy_from_slice(&header.data_size.to_le_bytes());
+        aad[23] = key_id;
+        aad
+    }
+
+    /// Checksum of an encrypted page as stored, so corruption is detected without the key
+    fn checksum(&self, header: &PageHeader, sealed: &[u8]) -> u32 {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&[header.page_type as u8]);
+        hasher.update(&header.version.0.to_le_bytes());
+        hasher.update(&header.ref_count.to_le_bytes());
+        hasher.update(&header.data_size.to_le_bytes());
+        hasher.update(&[self.key_id]);
+        hasher.update(&self.nonce);
+        hasher.update(sealed);
+        hasher.finalize()
+    }
+}
+
+/// A page as stored on disk; the data of an encrypted page is its ciphertext followed by the tag
+struct StoredPage {
+    page: Page,
+    seal: Option<PageSeal>,
+}
+
+impl StoredPage {
+    /// Returns the checksum mismatch for the page, if any
+    fn check(&self) -> Option<CorruptPage> {
+        let computed_checksum = match &self.seal {
+            Some(seal) => seal.checksum(&self.page.header, &self.page.data),
+            None => self.page.calculate_checksum(),
+        };
+        (computed_checksum != self.page.header.checksum).then(|| CorruptPage {
+            page_id: self.page.id,
+            page_type: self.page.header.page_type,
+            stored_checksum: self.page.header.checksum,
+            computed_checksum,
         })
     }
 }
 
 /// Storage file format manager
+///
+/// When the file is encrypted, every page written is sealed with the active data
+/// encryption key: the nonce and key ID take the reserved bytes of the page header and
+/// the authentication tag follows the data, leaving [`TAG_SIZE`] fewer bytes for data.
+/// Page checksums cover the encrypted bytes, so corruption is reported as such whether
+/// or not the key is at hand.
 pub struct FileFormat {
     /// Path to the storage file
     path: PathBuf,
@@ -335,6 +588,17 @@ pub struct FileFormat {
     header: FileHeader,
     /// Whether the file was newly created
     is_new: bool,
+    /// Whether the file was opened with direct IO, so every transfer must be aligned
+    direct_io: bool,
+    /// Receives the latency of every page write, used to back off background compaction
+    write_latency: Option<Arc<WriteLatencyMonitor>>,
+    /// Pages that failed checksum verification; reads fail fast until the page is rewritten.
+    /// Kept in the quarantine file too, so a restart does not read them again.
+    quarantined: HashMap<u64, CorruptPage>,
+    /// Wraps and unwraps the data encryption keys
+    key_provider: Option<Arc<dyn KeyProvider>>,
+    /// Unwrapped data encryption keys, set once an encrypted file is opened
+    keyring: Option<Keyring>,
 }
 
 /// FileFormat manages the storage file, including page allocation, reading, writing, and file metadata. It ensures data is stored and retrieved according to the defined format.
@@ -347,9 +611,26 @@ impl FileFormat {
             config,
             file: None,
             is_new: false,
+            direct_io: false,
+            write_latency: None,
+            quarantined: HashMap::new(),
+            key_provider: None,
+            keyring: None,
         }
     }
 
+    /// Supply the master key, before [`init`](Self::init). A new file is then created
+    /// encrypted; an existing unencrypted file stays so until
+    /// [`enable_encryption`](Self::enable_encryption).
+    pub fn set_key_provider(&mut self, provider: Arc<dyn KeyProvider>) {
+        self.key_provider = Some(provider);
+    }
+
+    /// Report page write latency to a compaction scheduler's monitor
+    pub fn set_write_latency_monitor(&mut self, monitor: Arc<WriteLatencyMonitor>) {
+        self.write_latency = Some(monitor);
+    }
+
     /// Initialize the storage file
     pub fn init(&mut self) -> StorageResult<()> {
         // Create the directory if it doesn't exist
@@ -361,8 +642,9 @@ impl FileFormat {
         let file_exists = self.path.exists();
         self.is_new = !file_exists;
 
-        // Open or create the file
-        let file = OpenOptions::new().read(true).write(true).create(true).open(&self.path)?;
+        // Open or create the file, bypassing the page cache if configured and supported
+        let (file, direct_io) = open_page_file(&self.path, self.config.direct_io)?;
+        self.direct_io = direct_io;
 
         // Store the file immediately so that it can be accessed later
         self.file = Some(file);
@@ -374,23 +656,215 @@ impl FileFormat {
             file.set_len(HEADER_SIZE as u64)?;
 
             // Write the header
-            let mut buffer = vec![0; HEADER_SIZE];
+            let mut buffer = AlignedBuffer::page_aligned(HEADER_SIZE);
             self.header.serialize(&mut buffer)?;
             file.write_all(&buffer)?;
+
+            if self.key_provider.is_some() {
+                self.activate_new_key()?;
+            }
+
+            // Pages quarantined in an earlier file of the same name are gone with it
+            self.save_quarantine()?;
         } else {
             // Read the header
             let file = self.file.as_mut().unwrap();
-            let mut buffer = vec![0; HEADER_SIZE];
+            let mut buffer = AlignedBuffer::page_aligned(HEADER_SIZE);
             file.read_exact(&mut buffer)?;
             self.header = FileHeader::deserialize(&buffer)?;
 
             // Update our config with the actual page size from the file
             self.config.page_size = self.header.page_size as usize;
+
+            // Unwrap the data encryption keys of an encrypted file
+            if let Some(encryption) = &self.header.encryption {
+                let provider = self.key_provider.as_ref().ok_or_else(|| StorageError::EncryptionKeyRequired { path: self.path.clone() })?;
+                self.keyring = Some(Keyring::unwrap(encryption, provider.as_ref())?);
+            }
+
+            self.load_quarantine()?;
+        }
+
+        // Direct IO transfers whole logical blocks at block-aligned offsets
+        if self.direct_io {
+            let block_size = logical_block_size(self.file.as_ref().unwrap());
+            check_direct_page_size(HEADER_SIZE, block_size)?;
+            check_direct_page_size(self.header.page_size as usize, block_size)?;
         }
 
         Ok(())
     }
 
+    /// Whether pages bypass the page cache. False when direct IO was not configured or
+    /// the filesystem does not support it.
+    pub fn direct_io_active(&self) -> bool {
+        self.direct_io
+    }
+
+    /// Read the encryption state of a storage file from its header, without the master key
+    pub fn read_encryption_status(path: &std::path::Path) -> StorageResult<EncryptionStatus> {
+        let mut buffer = vec![0; HEADER_SIZE];
+        File::open(path)?.read_exact(&mut buffer)?;
+        let header = FileHeader::deserialize(&buffer)?;
+        Ok(Self::status_of(&header))
+    }
+
+    fn status_of(header: &FileHeader) -> EncryptionStatus {
+        EncryptionStatus {
+            total_pages: header.total_pages.saturating_sub(1),
+            encryption: header.encryption.clone(),
+        }
+    }
+
+    /// Whether pages are written encrypted
+    pub fn is_encrypted(&self) -> bool {
+        self.keyring.is_some()
+    }
+
+    /// Encryption state of the open file
+    pub fn encryption_status(&self) -> EncryptionStatus {
+        Self::status_of(&self.header)
+    }
+
+    /// Encrypt an unencrypted file in place: pages are written encrypted from now on and
+    /// [`reencrypt_pages`](Self::reencrypt_pages) encrypts the existing ones. Returns the
+    /// ID of the new data encryption key.
+    pub fn enable_encryption(&mut self) -> StorageResult<u8> {
+        if self.header.encryption.is_some() {
+            return Err(StorageError::InvalidOperation(format!("{} is already encrypted", self.path.display())));
+        }
+        self.activate_new_key()
+    }
+
+    /// Activate a new data encryption key for all writes and start re-encrypting the
+    /// pages written with older keys. Returns the ID of the new key.
+    ///
+    /// A rotation started while another is running restarts from the first page, and
+    /// the keys of both stay in the keyring until it finishes.
+    pub fn rotate_key(&mut self) -> StorageResult<u8> {
+        if self.header.encryption.is_none() {
+            return Err(StorageError::InvalidOperation(format!("{} is not encrypted; enable encryption first", self.path.display())));
+        }
+        self.activate_new_key()
+    }
+
+    /// Generate, wrap and activate a data encryption key
+    fn activate_new_key(&mut self) -> StorageResult<u8> {
+        let provider = self
+            .key_provider
+            .clone()
+            .ok_or_else(|| StorageError::InvalidOperation("no master key was supplied to encrypt the storage file with".to_string()))?;
+
+        let mut encryption = self.header.encryption.clone().unwrap_or(EncryptionHeader {
+            active_key: 0,
+            keys: Vec::new(),
+            rotation: None,
+        });
+        let id = (1..=u8::MAX)
+            .find(|id| encryption.keys.iter().all(|key| key.id != *id))
+            .ok_or_else(|| StorageError::Encryption("every key ID is in use; let the running re-encryption finish first".to_string()))?;
+
+        let key = DataKey::generate();
+        encryption.keys.push(WrappedKey {
+            id,
+            created_at: generate_timestamp(),
+            wrapped: provider.wrap_key(&key)?,
+        });
+        encryption.active_key = id;
+        encryption.rotation = (self.header.total_pages > 1).then(|| RotationProgress {
+            next_page: 1,
+            ..RotationProgress::default()
+        });
+        if encryption.rotation.is_none() {
+            encryption.keys.retain(|key| key.id == id);
+        }
+
+        // The key must be on disk before any page is encrypted with it
+        let previous = self.header.encryption.replace(encryption);
+        if let Err(e) = self.write_header().and_then(|()| self.sync()) {
+            self.header.encryption = previous;
+            return Err(e);
+        }
+
+        match &mut self.keyring {
+            Some(keyring) => keyring.insert(id, &key),
+            None => self.keyring = Some(Keyring::new(id, &key)),
+        }
+        info!("Activated data encryption key {id} for {}", self.path.display());
+        Ok(id)
+    }
+
+    /// Re-encrypt up to `max_pages` pages with the active key, continuing the running
+    /// rotation. Returns `None` if no rotation is running.
+    ///
+    /// Progress is saved after each batch, so a rotation resumes where it stopped after a
+    /// restart. Once every page has been visited, keys no page needs any more are dropped
+    /// from the keyring, unless pages had to be skipped.
+    pub fn reencrypt_pages(&mut self, max_pages: u64) -> StorageResult<Option<ReencryptionBatch>> {
+        let (Some(keyring), Some(encryption)) = (&self.keyring, &self.header.encryption) else {
+            return Ok(None);
+        };
+        let Some(mut progress) = encryption.rotation else {
+            return Ok(None);
+        };
+        let active = keyring.active();
+
+        let end = progress.next_page.saturating_add(max_pages).min(self.header.total_pages);
+        for page_id in progress.next_page..end {
+            let stored = self.read_stored_page(PageId(page_id))?;
+            if stored.seal.is_some_and(|seal| seal.key_id == active) {
+                continue;
+            }
+            if let Some(corrupt) = stored.check() {
+                self.quarantine(&corrupt)?;
+                progress.pages_skipped += 1;
+                continue;
+            }
+
+            let mut page = self.open_page(stored)?;
+            match self.write_page(&mut page) {
+                Ok(()) => progress.pages_rewritten += 1,
+                Err(StorageError::InvalidOperation(reason)) => {
+                    warn!("Page {page_id} left as it is: {reason}");
+                    progress.pages_skipped += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        progress.next_page = end;
+
+        let finished = end >= self.header.total_pages;
+        let encryption = self.header.encryption.as_mut().expect("checked above");
+        if finished {
+            encryption.rotation = None;
+            if progress.pages_skipped == 0 {
+                encryption.keys.retain(|key| key.id == active);
+                self.keyring.as_mut().expect("checked above").retain(active);
+            }
+        } else {
+            encryption.rotation = Some(progress);
+        }
+
+        // Rewritten pages must be on disk before the progress, and above all the keys
+        // dropped, that the header records
+        self.sync()?;
+        self.write_header()?;
+        self.sync()?;
+
+        Ok(Some(ReencryptionBatch { progress, finished }))
+    }
+
+    /// Size to create pages with, leaving room for the authentication tag in encrypted files
+    fn new_page_size(&self) -> usize {
+        let page_size = self.header.page_size as usize;
+        if self.is_encrypted() { page_size - TAG_SIZE } else { page_size }
+    }
+
+    /// Number of data bytes a newly created page can hold
+    pub fn page_capacity(&self) -> usize {
+        self.new_page_size() - PageHeader::size()
+    }
+
     /// Check if the storage file is initialized
     pub fn is_initialized(&self) -> bool {
         self.file.is_some()
@@ -420,10 +894,10 @@ impl FileFormat {
     /// Write the header to disk
     fn write_header(&mut self) -> StorageResult<()> {
         if let Some(file) = &mut self.file {
-            let mut buffer = vec![0; HEADER_SIZE];
+            let mut buffer = AlignedBuffer::page_aligned(HEADER_SIZE);
             self.header.serialize(&mut buffer)?;
             file.seek(SeekFrom::Start(0))?;
-            file.write_all(&buffer)?;
+            failpoint::write_all(Failpoint::HeaderWrite, file, || self.path.clone(), &buffer)?;
             file.flush()?;
             Ok(())
         } else {
@@ -434,13 +908,43 @@ impl FileFormat {
     /// Reads a page from disk by its ID.
     ///
     /// Steps:
+    /// 1. Fail fast if the page is quarantined, without touching the disk.
+    /// 2. Read and deserialize the page (see `read_stored_page`).
+    /// 3. Verify the checksum for data integrity; on mismatch, quarantine the page
+    ///    and record it in the corruption log.
+    /// 4. Decrypt the page if it is encrypted (see `open_page`).
+    /// 5. Return the reconstructed Page object.
+    pub fn read_page(&mut self, id: PageId) -> StorageResult<Page> {
+        if let Some(corrupt) = self.quarantined.get(&id.0) {
+            return Err(StorageError::Corruption(format!("{} [quarantined]", corrupt.describe())));
+        }
+
+        let stored = self.read_stored_page(id)?;
+
+        if let Some(corrupt) = stored.check() {
+            let message = corrupt.describe();
+            self.quarantine(&corrupt)?;
+            return Err(StorageError::Corruption(message));
+        }
+
+        self.open_page(stored)
+    }
+
+    /// Reads and decrypts a page without verifying its checksum.
+    fn read_page_unverified(&mut self, id: PageId) -> StorageResult<Page> {
+        let stored = self.read_stored_page(id)?;
+        self.open_page(stored)
+    }
+
+    /// Reads a page as stored, without verifying its checksum or decrypting it.
+    ///
+    /// Steps:
     /// 1. Check if the page ID is valid (within total_pages).
     /// 2. Seek to the correct offset in the file.
     /// 3. Read the page data into a buffer.
-    /// 4. Deserialize the page header and data.
-    /// 5. Verify the checksum for data integrity.
-    /// 6. Return the reconstructed Page object.
-    pub fn read_page(&mut self, id: PageId) -> StorageResult<Page> {
+    /// 4. Deserialize the page header and data; the data of an encrypted page is its
+    ///    ciphertext followed by the authentication tag.
+    fn read_stored_page(&mut self, id: PageId) -> StorageResult<StoredPage> {
         if id.0 >= self.header.total_pages {
             return Err(StorageError::PageNotFound(id.0));
         }
@@ -456,12 +960,25 @@ impl FileFormat {
         file.seek(SeekFrom::Start(offset))?;
 
         let page_size = self.header.page_size as usize;
-        let mut buffer = vec![0; page_size];
+        let mut buffer = AlignedBuffer::page_aligned(page_size);
         file.read_exact(&mut buffer)?;
 
         // Parse header
         let header = PageHeader::deserialize(&buffer[0..PageHeader::size()])?;
 
+        if let Some(seal) = PageSeal::read(&buffer[0..PageHeader::size()]) {
+            let sealed_size = header.data_size as usize + TAG_SIZE;
+            let sealed = buffer.get(PageHeader::size()..PageHeader::size() + sealed_size).ok_or_else(|| {
+                StorageError::Corruption(format!(
+                    "Data size in header ({}) exceeds encrypted page data capacity ({})",
+                    header.data_size,
+                    page_size - PageHeader::size() - TAG_SIZE
+                ))
+            })?;
+            let page = Page { id, header, data: sealed.to_vec() };
+            return Ok(StoredPage { page, seal: Some(seal) });
+        }
+
         // Create the page with a fully zeroed data buffer of appropriate size
         let mut page = Page::new(id, header.page_type, header.version, page_size);
 
@@ -484,27 +1001,156 @@ impl FileFormat {
             }
         }
 
-        // Verify checksum
-        if !page.verify_checksum() {
-            return Err(StorageError::Corruption(format!("Page {} has invalid checksum", id.0)));
-        }
+        Ok(StoredPage { page, seal: None })
+    }
+
+    /// Decrypts a stored page. The decrypted page carries the checksum of its plaintext,
+    /// like any page in memory.
+    fn open_page(&self, stored: StoredPage) -> StorageResult<Page> {
+        let StoredPage { page: sealed, seal } = stored;
+        let Some(seal) = seal else {
+            return Ok(sealed);
+        };
+        let keyring = self.keyring.as_ref().ok_or_else(|| StorageError::EncryptionKeyRequired { path: self.path.clone() })?;
+
+        let aad = PageSeal::aad(sealed.id, &sealed.header, seal.key_id);
+        let plaintext = keyring.open(sealed.id, seal.key_id, &seal.nonce, &aad, &sealed.data)?;
 
+        let mut page = Page::new(sealed.id, sealed.header.page_type, sealed.header.version, self.header.page_size as usize - TAG_SIZE);
+        page.header = sealed.header;
+        page.data[..plaintext.len()].copy_from_slice(&plaintext);
+        page.update_checksum();
         Ok(page)
     }
 
+    /// Path of the corruption log kept next to the storage file
+    pub fn corruption_log_path(&self) -> PathBuf {
+        self.path.with_extension(CORRUPTION_LOG_EXTENSION)
+    }
+
+    /// Whether a page has been quarantined after a failed checksum verification
+    pub fn is_quarantined(&self, id: PageId) -> bool {
+        self.quarantined.contains_key(&id.0)
+    }
+
+    /// Quarantined page IDs, in ascending order
+    pub fn quarantined_pages(&self) -> Vec<PageId> {
+        let mut pages: Vec<PageId> = self.quarantined.keys().copied().map(PageId).collect();
+        pages.sort_by_key(|page| page.0);
+        pages
+    }
+
+    /// File listing the quarantined pages of the storage file at `path`
+    pub fn quarantine_path(path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_os_string();
+        name.push(format!(".{QUARANTINE_SUFFIX}"));
+        PathBuf::from(name)
+    }
+
+    /// Read the pages quarantined before the file was last closed
+    fn load_quarantine(&mut self) -> StorageResult<()> {
+        let path = Self::quarantine_path(&self.path);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            let corrupt = CorruptPage::parse_quarantine_entry(line).ok_or_else(|| StorageError::Corruption(format!("Invalid quarantine entry '{line}' in {}", path.display())))?;
+            self.quarantined.insert(corrupt.page_id.0, corrupt);
+        }
+        Ok(())
+    }
+
+    /// Replace the quarantine file with the pages quarantined now, removing it when there are none
+    fn save_quarantine(&self) -> StorageResult<()> {
+        let path = Self::quarantine_path(&self.path);
+        if self.quarantined.is_empty() {
+            return match std::fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            };
+        }
+
+        let mut pages: Vec<&CorruptPage> = self.quarantined.values().collect();
+        pages.sort_by_key(|page| page.page_id.0);
+        let contents: String = pages.iter().map(|page| page.quarantine_entry() + "\n").collect();
+
+        let tmp_path = path.with_extension(format!("{QUARANTINE_SUFFIX}.tmp"));
+        std::fs::write(&tmp_path, contents)?;
+        File::open(&tmp_path)?.sync_all()?;
+        std::fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    /// Lift the quarantine of a page whose content verified or was replaced
+    fn release(&mut self, id: PageId) -> StorageResult<()> {
+        if self.quarantined.remove(&id.0).is_some() {
+            self.save_quarantine()?;
+        }
+        Ok(())
+    }
+
+    /// Quarantine a page and append it to the corruption log. Pages already
+    /// quarantined are not logged again.
+    fn quarantine(&mut self, corrupt: &CorruptPage) -> StorageResult<()> {
+        if self.quarantined.insert(corrupt.page_id.0, corrupt.clone()).is_some() {
+            return Ok(());
+        }
+        self.save_quarantine()?;
+
+        let mut log = OpenOptions::new().create(true).append(true).open(self.corruption_log_path())?;
+        writeln!(
+            log,
+            "{} page={} type={:?} stored={:08x} computed={:08x}",
+            generate_timestamp(),
+            corrupt.page_id.0,
+            corrupt.page_type,
+            corrupt.stored_checksum,
+            corrupt.computed_checksum
+        )?;
+        log.flush()?;
+        Ok(())
+    }
+
+    /// Scans every page in the file, quarantining and logging any whose checksum
+    /// does not match. Quarantined pages are re-read too, and released if they
+    /// now verify, so the report reflects the current state of the disk.
+    pub fn verify(&mut self) -> StorageResult<VerifyReport> {
+        let mut report = VerifyReport::default();
+
+        // Skip page ID 0 which is the header
+        for page_id in 1..self.header.total_pages {
+            let stored = self.read_stored_page(PageId(page_id))?;
+            report.pages_scanned += 1;
+
+            if let Some(corrupt) = stored.check() {
+                self.quarantine(&corrupt)?;
+                report.corrupt_pages.push(corrupt);
+                continue;
+            }
+
+            self.release(PageId(page_id))?;
+            if stored.page.header.page_type == PageType::Free {
+                report.free_pages += 1;
+            }
+        }
+
+        Ok(report)
+    }
+
     /// Writes a page to disk.
     ///
     /// Steps:
     /// 1. If the page is new, extend the file and update the header.
     /// 2. Seek to the correct offset for the page.
-    /// 3. Serialize the header and data into a buffer.
+    /// 3. Update the checksum, then serialize the header and data into a buffer,
+    ///    encrypting the data if the file is encrypted.
     /// 4. Write the buffer to disk and flush.
-    /// 5. Return Ok or error.
+    /// 5. Lift any quarantine on the page, since its content has been replaced.
+    /// 6. Return Ok or error.
     pub fn write_page(&mut self, page: &mut Page) -> StorageResult<()> {
-        // We don't call update_checksum() here anymore since:
-        // 1. It's the caller's responsibility to ensure checksum is updated
-        // 2. Calling it twice would make tests fail because the second call would checksum differently
-        // 3. The caller will typically have more context about when the checksum needs updating
+        let started = Instant::now();
 
         if !self.is_initialized() {
             return Err(StorageError::Io(io::Error::new(io::ErrorKind::NotConnected, "File not initialized")));
@@ -527,37 +1173,78 @@ impl FileFormat {
             file.set_len(new_size)?;
 
             // Write the header
-            let mut header_buffer = vec![0; HEADER_SIZE];
+            let mut header_buffer = AlignedBuffer::page_aligned(HEADER_SIZE);
             self.header.serialize(&mut header_buffer)?;
             file.seek(SeekFrom::Start(0))?;
-            file.write_all(&header_buffer)?;
+            failpoint::write_all(Failpoint::HeaderWrite, file, || self.path.clone(), &header_buffer)?;
         }
 
         // Skip the header page for calculation
         let offset = if page.id.0 == 0 { 0 } else { HEADER_SIZE as u64 + (page.id.0 - 1) * self.header.page_size as u64 };
 
-        // Prepare the buffer for the page
-        let mut buffer = vec![0; page_size];
+        // Prepare the buffer for the page, aligned in case the file is opened with direct IO
+        let mut buffer = AlignedBuffer::page_aligned(page_size);
+
+        if self.is_encrypted() {
+            self.fit_for_tag(page)?;
+        }
+
+        // Stamp the checksum so every page on disk can be verified on read
+        page.update_checksum();
 
         // Write header to buffer
         page.header.serialize(&mut buffer[0..PageHeader::size()])?;
 
         // Write data to buffer - only write up to data_size bytes
         let data_size = page.header.data_size as usize;
-        if data_size > 0 {
-            // Make sure we don't try to copy more data than the page contains
-            let actual_size = std::cmp::min(data_size, page.data.len());
-            if actual_size > 0 {
-                buffer[PageHeader::size()..PageHeader::size() + actual_size].copy_from_slice(&page.data[0..actual_size]);
-            }
+        // Make sure we don't try to copy more data than the page contains
+        let actual_size = std::cmp::min(data_size, page.data.len());
+        if let Some(keyring) = &self.keyring {
+            // Seal the data, then checksum what is stored in place of the plaintext checksum
+            let mut plaintext = vec![0; data_size];
+            plaintext[..actual_size].copy_from_slice(&page.data[..actual_size]);
+            let key_id = keyring.active();
+            let (nonce, sealed) = keyring.seal(page.id, &PageSeal::aad(page.id, &page.header, key_id), &plaintext)?;
+            let seal = PageSeal { key_id, nonce };
+            seal.write(&mut buffer[0..PageHeader::size()]);
+            buffer[13..17].copy_from_slice(&seal.checksum(&page.header, &sealed).to_le_bytes());
+            buffer[PageHeader::size()..PageHeader::size() + sealed.len()].copy_from_slice(&sealed);
+        } else if actual_size > 0 {
+            buffer[PageHeader::size()..PageHeader::size() + actual_size].copy_from_slice(&page.data[0..actual_size]);
         }
 
         // Get file reference and write to disk
         let file = self.file.as_mut().unwrap();
         file.seek(SeekFrom::Start(offset))?;
-        file.write_all(&buffer)?;
+        failpoint::write_all(Failpoint::PageWrite, file, || self.path.clone(), &buffer)?;
         file.flush()?;
+        self.release(page.id)?;
+
+        if let Some(monitor) = &self.write_latency {
+            monitor.record(started.elapsed());
+        }
+
+        Ok(())
+    }
 
+    /// Make room for the authentication tag of an encrypted page. Pages created before
+    /// the file was encrypted span the whole page; their data size is shortened when the
+    /// bytes given up are zero, as reads zero-fill past the data size anyway.
+    fn fit_for_tag(&self, page: &mut Page) -> StorageResult<()> {
+        let capacity = self.header.page_size as usize - PageHeader::size() - TAG_SIZE;
+        let data_size = page.header.data_size as usize;
+        if data_size <= capacity {
+            return Ok(());
+        }
+
+        let end = data_size.min(page.data.len());
+        if page.data.get(capacity..end).is_some_and(|tail| tail.iter().any(|&byte| byte != 0)) {
+            return Err(StorageError::InvalidOperation(format!(
+                "page {} holds {data_size} bytes, but an encrypted page has room for {capacity}",
+                page.id.0
+            )));
+        }
+        page.header.data_size = capacity as u16;
         Ok(())
     }
 
@@ -574,44 +1261,9 @@ impl FileFormat {
             // Reuse a free page
             let free_page_id = self.header.first_free_page;
 
-            // Read the free page - if this fails due to checksum, we'll try to repair it
-            let free_page = match self.read_page(free_page_id) {
-                Ok(page) => page,
-                Err(StorageError::Corruption(msg)) if msg.contains("invalid checksum") => {
-                    // Try to recover by reading the page directly without checksum verification
-                    let file = self
-                        .file
-                        .as_mut()
-                        .ok_or_else(|| StorageError::Io(io::Error::new(io::ErrorKind::NotConnected, "File not initialized")))?;
-
-                    // Skip the header page
-                    let offset = if free_page_id.0 == 0 {
-                        0
-                    } else {
-                        HEADER_SIZE as u64 + (free_page_id.0 - 1) * self.header.page_size as u64
-                    };
-                    file.seek(SeekFrom::Start(offset))?;
-
-                    let page_size = self.header.page_size as usize;
-                    let mut buffer = vec![0; page_size];
-                    file.read_exact(&mut buffer)?;
-
-                    // Parse header
-                    let header = PageHeader::deserialize(&buffer[0..PageHeader::size()])?;
-
-                    // Create a new page with recovered data
-                    let mut recovered_page = Page::new(free_page_id, header.page_type, header.version, page_size);
-                    recovered_page.header.data_size = header.data_size;
-
-                    if header.data_size > 0 {
-                        let data_size = header.data_size as usize;
-                        recovered_page.data[0..data_size].copy_from_slice(&buffer[PageHeader::size()..PageHeader::size() + data_size]);
-                    }
-
-                    recovered_page
-                }
-                Err(e) => return Err(e),
-            };
+            // Only the free list pointer is needed and the page is about to be overwritten,
+            // so a checksum mismatch here is not worth quarantining the page for
+            let free_page = self.read_page_unverified(free_page_id)?;
 
             // Update the free list
             if free_page.data.len() >= 8 {
@@ -621,10 +1273,7 @@ impl FileFormat {
             }
 
             // Create a new page with the same ID
-            let mut page = Page::new(free_page_id, page_type, version, self.header.page_size as usize);
-
-            // Update checksum before writing
-            page.update_checksum();
+            let mut page = Page::new(free_page_id, page_type, version, self.new_page_size());
 
             // Write the page to disk
             self.write_page(&mut page)?;
@@ -633,10 +1282,7 @@ impl FileFormat {
         } else {
             // Allocate a new page at the end of the file
             let page_id = PageId(self.header.total_pages);
-            let mut page = Page::new(page_id, page_type, version, self.header.page_size as usize);
-
-            // Update checksum before writing
-            page.update_checksum();
+            let mut page = Page::new(page_id, page_type, version, self.new_page_size());
 
             // Write the page to disk
             self.write_page(&mut page)?;
@@ -658,7 +1304,7 @@ impl FileFormat {
         }
 
         // Create a free page
-        let mut page = Page::new(id, PageType::Free, VersionId(0), self.header.page_size as usize);
+        let mut page = Page::new(id, PageType::Free, VersionId(0), self.new_page_size());
 
         // Add it to the free list
         let next_free = self.header.first_free_page.0.to_le_bytes();
@@ -667,9 +1313,6 @@ impl FileFormat {
         // Update the page data size to include the free list pointer
         page.header.data_size = 8;
 
-        // Update the checksum before writing
-        page.update_checksum();
-
         // Update the header
         self.header.first_free_page = id;
         self.write_header()?;
@@ -682,8 +1325,8 @@ impl FileFormat {
 
     /// Sync all changes to disk
     pub fn sync(&mut self) -> StorageResult<()> {
-        if let Some(file) = &mut self.file {
-            file.sync_all()?;
+        if let Some(file) = &self.file {
+            failpoint::sync_all(Failpoint::DataSync, file, || self.path.clone())?;
             Ok(())
         } else {
             Err(StorageError::Io(io::Error::new(io::ErrorKind::NotConnected, "File not initialized")))
@@ -701,6 +1344,8 @@ impl FileFormat {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage_engine::encryption::MasterKey;
+    use crate::storage_engine::eviction::ReplacementPolicy;
     use tempfile::tempdir;
 
     #[test]
@@ -734,6 +1379,7 @@ mod tests {
             total_pages: 100,
             current_version: VersionId(5),
             first_free_page: PageId(10),
+            encryption: None,
         };
 
         let mut buffer = vec![0; HEADER_SIZE];
@@ -789,11 +1435,16 @@ mod tests {
             path: file_path,
             page_size: 512,
             buffer_pool_size: 100,
+            eviction_policy: ReplacementPolicy::LRU,
             direct_io: false,
+            io_backend: Default::default(),
             wal_size: 1024 * 1024,
             flush_interval_ms: 100,
             max_dirty_pages: 10,
             writer_threads: 1,
+            compaction_threads: None,
+            compaction_io_budget_bytes_per_sec: 0,
+            deadlock_policy: Default::default(),
         };
 
         let mut file_format = FileFormat::new(config);
@@ -821,11 +1472,16 @@ mod tests {
             path: file_path,
             page_size: 512,
             buffer_pool_size: 100,
+            eviction_policy: ReplacementPolicy::LRU,
             direct_io: false,
+            io_backend: Default::default(),
             wal_size: 1024 * 1024,
             flush_interval_ms: 100,
             max_dirty_pages: 10,
             writer_threads: 1,
+            compaction_threads: None,
+            compaction_io_budget_bytes_per_sec: 0,
+            deadlock_policy: Default::default(),
         };
 
         let mut file_format = FileFormat::new(config);
@@ -855,11 +1511,16 @@ mod tests {
             path: path.clone(),
             page_size: 4096,
             buffer_pool_size: 100,
+            eviction_policy: ReplacementPolicy::LRU,
             direct_io: false,
+            io_backend: Default::default(),
             wal_size: 1024 * 1024,
             flush_interval_ms: 100,
             max_dirty_pages: 10,
             writer_threads: 1,
+            compaction_threads: None,
+            compaction_io_budget_bytes_per_sec: 0,
+            deadlock_policy: Default::default(),
         };
 
         // Create and initialize FileFormat
@@ -910,6 +1571,45 @@ mod tests {
         assert!(file_format.close().is_ok());
     }
 
+    #[test]
+    fn test_direct_io_pages_round_trip() {
+        let dir = tempdir().unwrap();
+        let config = StorageConfig {
+            path: dir.path().join("direct.db"),
+            direct_io: true,
+            ..Default::default()
+        };
+
+        let mut file_format = FileFormat::new(config.clone());
+        file_format.init().unwrap();
+        let mut ids = Vec::new();
+        for i in 0..4u8 {
+            let mut page = file_format.allocate_page(PageType::Data, VersionId(1)).unwrap();
+            page.data[..3].copy_from_slice(&[i; 3]);
+            page.header.data_size = 3;
+            file_format.write_page(&mut page).unwrap();
+            ids.push(page.id);
+        }
+        file_format.close().unwrap();
+
+        // Reopen, with direct IO if the filesystem supports it
+        let mut file_format = FileFormat::new(config.clone());
+        file_format.init().unwrap();
+        for (i, id) in ids.into_iter().enumerate() {
+            assert_eq!(&file_format.read_page(id).unwrap().data[..3], &[i as u8; 3]);
+        }
+
+        // Direct IO moves whole blocks, so pages smaller than a block are refused
+        if file_format.direct_io_active() {
+            let mut small = FileFormat::new(StorageConfig {
+                path: dir.path().join("small.db"),
+                page_size: 512,
+                ..config
+            });
+            assert!(matches!(small.init(), Err(StorageError::InvalidConfig { .. })));
+        }
+    }
+
     #[test]
     fn test_checksum_serialization() {
         // Create a page and test direct serialization/deserialization to ensure checksum integrity
@@ -963,11 +1663,16 @@ mod tests {
             path: file_path.clone(),
             page_size: 4096,
             buffer_pool_size: 100,
+            eviction_policy: ReplacementPolicy::LRU,
             direct_io: false,
+            io_backend: Default::default(),
             wal_size: 1024 * 1024,
             flush_interval_ms: 100,
             max_dirty_pages: 10,
             writer_threads: 1,
+            compaction_threads: None,
+            compaction_io_budget_bytes_per_sec: 0,
+            deadlock_policy: Default::default(),
         };
 
         let mut file_format = FileFormat::new(config);
@@ -1008,11 +1713,16 @@ mod tests {
             path: file_path.clone(),
             page_size: 4096,
             buffer_pool_size: 100,
+            eviction_policy: ReplacementPolicy::LRU,
             direct_io: false,
+            io_backend: Default::default(),
             wal_size: 1024 * 1024,
             flush_interval_ms: 100,
             max_dirty_pages: 10,
             writer_threads: 1,
+            compaction_threads: None,
+            compaction_io_budget_bytes_per_sec: 0,
+            deadlock_policy: Default::default(),
         };
 
         let mut file_format = FileFormat::new(config);
@@ -1046,4 +1756,316 @@ mod tests {
         // Close file
         assert!(file_format.close().is_ok());
     }
+
+    fn corrupt_byte(path: &std::path::Path, page_id: u64, offset_in_page: usize) {
+        let mut file = OpenOptions::new().read(true).write(true).open(path).unwrap();
+        let offset = HEADER_SIZE as u64 + (page_id - 1) * 4096 + offset_in_page as u64;
+        let mut byte = [0u8; 1];
+        file.seek(SeekFrom::Start(offset)).unwrap();
+        file.read_exact(&mut byte).unwrap();
+        file.seek(SeekFrom::Start(offset)).unwrap();
+        file.write_all(&[byte[0] ^ 0xFF]).unwrap();
+        file.sync_all().unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_page_is_quarantined() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("corrupt_test.dotdb");
+
+        let config = StorageConfig {
+            path: file_path.clone(),
+            page_size: 4096,
+            buffer_pool_size: 100,
+            eviction_policy: ReplacementPolicy::LRU,
+            direct_io: false,
+            io_backend: Default::default(),
+            wal_size: 1024 * 1024,
+            flush_interval_ms: 100,
+            max_dirty_pages: 10,
+            writer_threads: 1,
+            compaction_threads: None,
+            compaction_io_budget_bytes_per_sec: 0,
+            deadlock_policy: Default::default(),
+        };
+
+        let mut file_format = FileFormat::new(config.clone());
+        assert!(file_format.init().is_ok());
+
+        // write_page stamps the checksum itself
+        let mut page = file_format.allocate_page(PageType::Data, VersionId(1)).unwrap();
+        let data = b"primary data";
+        page.data[0..data.len()].copy_from_slice(data);
+        page.header.data_size = data.len() as u16;
+        assert!(file_format.write_page(&mut page).is_ok());
+        assert!(file_format.read_page(page.id).is_ok());
+
+        corrupt_byte(&file_path, page.id.0, PageHeader::size() + 3);
+
+        match file_format.read_page(page.id) {
+            Err(StorageError::Corruption(msg)) => {
+                assert!(msg.starts_with("Page 1 has invalid checksum"), "{msg}");
+                assert!(!msg.contains("rebuild"));
+            }
+            other => panic!("expected corruption error, got {other:?}"),
+        }
+        assert!(file_format.is_quarantined(page.id));
+        let log = std::fs::read_to_string(file_format.corruption_log_path()).unwrap();
+        assert_eq!(log.lines().count(), 1);
+        assert!(log.contains("page=1 type=Data"));
+
+        // Repairing the bytes on disk does not help: quarantined reads never touch the file,
+        // not even after the file is opened again
+        corrupt_byte(&file_path, page.id.0, PageHeader::size() + 3);
+        assert!(file_format.close().is_ok());
+        let mut file_format = FileFormat::new(config.clone());
+        assert!(file_format.init().is_ok());
+        assert_eq!(file_format.quarantined_pages(), vec![page.id]);
+        match file_format.read_page(page.id) {
+            Err(StorageError::Corruption(msg)) => assert!(msg.contains("quarantined"), "{msg}"),
+            other => panic!("expected quarantine error, got {other:?}"),
+        }
+
+        // Rewriting the page lifts the quarantine for good
+        assert!(file_format.write_page(&mut page).is_ok());
+        assert!(!file_format.is_quarantined(page.id));
+        assert_eq!(&file_format.read_page(page.id).unwrap().data[0..data.len()], data);
+        assert!(!FileFormat::quarantine_path(&file_path).exists());
+        assert!(file_format.close().is_ok());
+        let mut file_format = FileFormat::new(config);
+        assert!(file_format.init().is_ok());
+        assert!(file_format.quarantined_pages().is_empty());
+    }
+
+    #[test]
+    fn test_verify_reports_corrupt_index_pages() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("verify_test.dotdb");
+
+        let config = StorageConfig {
+            path: file_path.clone(),
+            page_size: 4096,
+            buffer_pool_size: 100,
+            eviction_policy: ReplacementPolicy::LRU,
+            direct_io: false,
+            io_backend: Default::default(),
+            wal_size: 1024 * 1024,
+            flush_interval_ms: 100,
+            max_dirty_pages: 10,
+            writer_threads: 1,
+            compaction_threads: None,
+            compaction_io_budget_bytes_per_sec: 0,
+            deadlock_policy: Default::default(),
+        };
+
+        let mut file_format = FileFormat::new(config);
+        assert!(file_format.init().is_ok());
+
+        let data_page = file_format.allocate_page(PageType::Data, VersionId(1)).unwrap();
+        let mut index_page = file_format.allocate_page(PageType::Index, VersionId(1)).unwrap();
+        let freed = file_format.allocate_page(PageType::Data, VersionId(1)).unwrap();
+        index_page.data[0..5].copy_from_slice(b"index");
+        index_page.header.data_size = 5;
+        assert!(file_format.write_page(&mut index_page).is_ok());
+        assert!(file_format.free_page(freed.id).is_ok());
+
+        let report = file_format.verify().unwrap();
+        assert!(report.is_clean());
+        assert_eq!(report.pages_scanned, 3);
+        assert_eq!(report.free_pages, 1);
+
+        corrupt_byte(&file_path, index_page.id.0, PageHeader::size());
+
+        let report = file_format.verify().unwrap();
+        assert_eq!(report.corrupt_pages.len(), 1);
+        assert_eq!(report.corrupt_index_pages().count(), 1);
+        assert_eq!(report.corrupt_pages[0].page_id, index_page.id);
+        assert!(file_format.is_quarantined(index_page.id));
+        assert!(!file_format.is_quarantined(data_page.id));
+
+        match file_format.read_page(index_page.id) {
+            Err(StorageError::Corruption(msg)) => assert!(msg.contains("quarantined") && msg.contains("rebuild"), "{msg}"),
+            other => panic!("expected quarantine error, got {other:?}"),
+        }
+        let message = report.corrupt_pages[0].describe();
+        assert!(message.starts_with("Index page"));
+        assert!(message.contains("rebuild"));
+
+        // A second scan does not log the page twice
+        file_format.verify().unwrap();
+        let log = std::fs::read_to_string(file_format.corruption_log_path()).unwrap();
+        assert_eq!(log.lines().count(), 1);
+    }
+
+    fn encrypted_config(path: PathBuf) -> StorageConfig {
+        StorageConfig {
+            path,
+            page_size: 4096,
+            ..StorageConfig::default()
+        }
+    }
+
+    fn open_with_key(path: &std::path::Path, key: [u8; 32]) -> StorageResult<FileFormat> {
+        let mut file_format = FileFormat::new(encrypted_config(path.to_path_buf()));
+        file_format.set_key_provider(Arc::new(MasterKey::from_bytes(key)));
+        file_format.init()?;
+        Ok(file_format)
+    }
+
+    fn write_text(file_format: &mut FileFormat, text: &[u8]) -> PageId {
+        let mut page = file_format.allocate_page(PageType::Data, VersionId(1)).unwrap();
+        page.data[..text.len()].copy_from_slice(text);
+        page.header.data_size = text.len() as u16;
+        file_format.write_page(&mut page).unwrap();
+        page.id
+    }
+
+    fn read_text(file_format: &mut FileFormat, id: PageId) -> Vec<u8> {
+        let page = file_format.read_page(id).unwrap();
+        assert!(page.verify_checksum());
+        page.data[..page.header.data_size as usize].to_vec()
+    }
+
+    #[test]
+    fn test_encrypted_pages_round_trip_without_plaintext_on_disk() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("encrypted.dotdb");
+
+        let mut file_format = open_with_key(&path, [7; 32]).unwrap();
+        assert!(file_format.is_encrypted());
+        let id = write_text(&mut file_format, b"alice@example.com");
+        let allocated = file_format.allocate_page(PageType::Data, VersionId(1)).unwrap();
+        assert_eq!(allocated.data.len(), 4096 - PageHeader::size() - TAG_SIZE);
+        file_format.close().unwrap();
+
+        let raw = std::fs::read(&path).unwrap();
+        assert!(!raw.windows(17).any(|window| window == b"alice@example.com"));
+        assert_eq!(u32::from_le_bytes(raw[4..8].try_into().unwrap()), ENCRYPTED_FORMAT_VERSION);
+
+        let mut file_format = open_with_key(&path, [7; 32]).unwrap();
+        assert_eq!(read_text(&mut file_format, id), b"alice@example.com");
+        assert!(file_format.verify().unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_encrypted_file_requires_the_right_key() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("locked.dotdb");
+        let mut file_format = open_with_key(&path, [1; 32]).unwrap();
+        write_text(&mut file_format, b"secret");
+        file_format.close().unwrap();
+
+        let mut without_key = FileFormat::new(encrypted_config(path.clone()));
+        match without_key.init() {
+            Err(StorageError::EncryptionKeyRequired { path: locked }) => assert_eq!(locked, path),
+            other => panic!("expected a missing key error, got {:?}", other.err()),
+        }
+        assert!(matches!(open_with_key(&path, [2; 32]), Err(StorageError::Encryption(_))));
+
+        // The header can still be inspected without the key
+        let status = FileFormat::read_encryption_status(&path).unwrap();
+        assert!(status.is_encrypted());
+        assert_eq!(status.total_pages, 1);
+        assert_eq!(status.encryption.unwrap().keys.len(), 1);
+    }
+
+    #[test]
+    fn test_corrupt_encrypted_page_is_reported_as_corruption() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("corrupt_encrypted.dotdb");
+        let mut file_format = open_with_key(&path, [3; 32]).unwrap();
+        let id = write_text(&mut file_format, b"ciphertext is checksummed");
+
+        corrupt_byte(&path, id.0, PageHeader::size() + 4);
+
+        let report = file_format.verify().unwrap();
+        assert_eq!(report.corrupt_pages.len(), 1);
+        assert!(matches!(file_format.read_page(id), Err(StorageError::Corruption(_))));
+    }
+
+    #[test]
+    fn test_unencrypted_file_is_encrypted_in_place() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("upgrade.dotdb");
+        let mut plain = FileFormat::new(encrypted_config(path.clone()));
+        plain.init().unwrap();
+        let ids: Vec<PageId> = (0..5).map(|i| write_text(&mut plain, format!("record {i}").as_bytes())).collect();
+        // A fresh page spans the whole page, but only holds zeros past its content
+        let full = plain.allocate_page(PageType::Data, VersionId(1)).unwrap();
+        let mut packed = plain.allocate_page(PageType::Data, VersionId(1)).unwrap();
+        packed.data.fill(0xAB);
+        plain.write_page(&mut packed).unwrap();
+        plain.close().unwrap();
+
+        let mut file_format = FileFormat::new(encrypted_config(path.clone()));
+        file_format.set_key_provider(Arc::new(MasterKey::from_bytes([4; 32])));
+        file_format.init().unwrap();
+        assert!(!file_format.is_encrypted());
+        assert!(matches!(file_format.rotate_key(), Err(StorageError::InvalidOperation(_))));
+        assert_eq!(file_format.enable_encryption().unwrap(), 1);
+        assert!(matches!(file_format.enable_encryption(), Err(StorageError::InvalidOperation(_))));
+
+        // Plaintext pages stay readable while they wait to be encrypted
+        assert_eq!(read_text(&mut file_format, ids[0]), b"record 0");
+        let status = file_format.encryption_status();
+        assert_eq!(status.rotation_percent(), Some(0.0));
+
+        let batch = file_format.reencrypt_pages(4).unwrap().unwrap();
+        assert!(!batch.finished);
+        assert_eq!(batch.progress.next_page, 5);
+        assert_eq!(file_format.encryption_status().rotation_percent(), Some(4.0 * 100.0 / 7.0));
+
+        let batch = file_format.reencrypt_pages(100).unwrap().unwrap();
+        assert!(batch.finished);
+        assert_eq!(batch.progress.pages_rewritten, 6);
+        assert_eq!(batch.progress.pages_skipped, 1);
+        assert!(file_format.reencrypt_pages(100).unwrap().is_none());
+        file_format.close().unwrap();
+
+        let mut file_format = open_with_key(&path, [4; 32]).unwrap();
+        for (i, id) in ids.iter().enumerate() {
+            assert_eq!(read_text(&mut file_format, *id), format!("record {i}").as_bytes());
+        }
+        let full = file_format.read_page(full.id).unwrap();
+        assert_eq!(full.header.data_size as usize, 4096 - PageHeader::size() - TAG_SIZE);
+        // The packed page could not make room for the tag and is left as it was
+        let packed = file_format.read_page(packed.id).unwrap();
+        assert!(packed.data.iter().all(|&byte| byte == 0xAB));
+        assert!(file_format.verify().unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_key_rotation_resumes_and_retires_old_keys() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("rotate.dotdb");
+        let mut file_format = open_with_key(&path, [5; 32]).unwrap();
+        let ids: Vec<PageId> = (0..6).map(|i| write_text(&mut file_format, format!("row {i}").as_bytes())).collect();
+
+        assert_eq!(file_format.rotate_key().unwrap(), 2);
+        file_format.reencrypt_pages(3).unwrap();
+        // New writes use the new key straight away
+        let late = write_text(&mut file_format, b"written after rotation");
+        file_format.close().unwrap();
+
+        // Progress survives a restart
+        let mut file_format = open_with_key(&path, [5; 32]).unwrap();
+        let encryption = file_format.encryption_status().encryption.unwrap();
+        assert_eq!(encryption.active_key, 2);
+        assert_eq!(encryption.keys.iter().map(|key| key.id).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(encryption.rotation.unwrap().next_page, 4);
+
+        let batch = file_format.reencrypt_pages(100).unwrap().unwrap();
+        assert!(batch.finished);
+        assert_eq!(batch.progress.pages_rewritten, 6);
+        let encryption = file_format.encryption_status().encryption.unwrap();
+        assert_eq!(encryption.keys.iter().map(|key| key.id).collect::<Vec<_>>(), vec![2]);
+        assert!(encryption.rotation.is_none());
+
+        for (i, id) in ids.iter().enumerate() {
+            assert_eq!(read_text(&mut file_format, *id), format!("row {i}").as_bytes());
+        }
+        assert_eq!(read_text(&mut file_format, late), b"written after rotation");
+        // Key IDs of retired keys are reused
+        assert_eq!(file_format.rotate_key().unwrap(), 1);
+    }
 }
@@ -0,0 +1,340 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+// WAL group commit module
+// Concurrent committers enqueue their log records and one of them, the leader, writes the whole batch and makes it durable with a single fsync. A committer only returns once the batch containing its record has been synced, and a failed flush is reported to every committer in the batch.
+
+use std::collections::HashMap;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::storage_engine::lib::{StorageError, StorageResult};
+use crate::storage_engine::wal::{LogEntry, LogSequenceNumber};
+
+/// Configuration for WAL group commit
+#[derive(Debug, Clone)]
+pub struct GroupCommitConfig {
+    /// How long a leader waits for more committers before flushing. The wait only happens
+    /// when other commits are already queued, so a lone writer pays no extra latency.
+    pub max_wait: Duration,
+    /// Flush as soon as this many record bytes are queued, without waiting further
+    pub max_batch_bytes: usize,
+}
+
+impl Default for GroupCommitConfig {
+    fn default() -> Self {
+        Self {
+            max_wait: Duration::from_millis(2),
+            max_batch_bytes: 1024 * 1024, // 1 MB
+        }
+    }
+}
+
+/// Statistics about group commit batches
+#[derive(Debug, Clone, Default)]
+pub struct GroupCommitStats {
+    /// Number of batches flushed, each with a single fsync
+    pub batches: u64,
+    /// Number of records committed through batches
+    pub records: u64,
+    /// Number of record bytes committed through batches
+    pub bytes: u64,
+    /// Number of batches whose flush failed
+    pub failed_batches: u64,
+}
+
+impl GroupCommitStats {
+    /// Average number of records per flushed batch
+    pub fn average_batch_size(&self) -> f64 {
+        if self.batches == 0 { 0.0 } else { self.records as f64 / self.batches as f64 }
+    }
+}
+
+/// A record waiting for a leader
+struct PendingRecord {
+    ticket: u64,
+    entry: LogEntry,
+    size: usize,
+}
+
+#[derive(Default)]
+struct QueueState {
+    pending: Vec<PendingRecord>,
+    pending_bytes: usize,
+    /// Whether a committer is currently collecting or flushing a batch
+    leader_active: bool,
+    next_ticket: u64,
+    /// Outcomes of flushed records not yet picked up by their committer
+    completed: HashMap<u64, StorageResult<LogSequenceNumber>>,
+    stats: GroupCommitStats,
+}
+
+/// Batches concurrent commits so that a single fsync covers many records
+pub struct GroupCommitter {
+    config: GroupCommitConfig,
+    state: Mutex<QueueState>,
+    changed: Condvar,
+    /// Fails the next batch before anything is written, as if the process died after enqueue
+    #[cfg(test)]
+    pub(crate) crash_before_flush: std::sync::atomic::AtomicBool,
+}
+
+impl GroupCommitter {
+    /// Create a new group committer
+    pub fn new(config: GroupCommitConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(QueueState::default()),
+            changed: Condvar::new(),
+            #[cfg(test)]
+            crash_before_flush: std::sync::atomic::AtomicBool::new(false),
+        }
+    }
+
+    /// Get the group commit statistics
+    pub fn get_statistics(&self) -> GroupCommitStats {
+        self.state.lock().unwrap().stats.clone()
+    }
+
+    /// Enqueues `entry` and blocks until the batch containing it is durable.
+    ///
+    /// Steps:
+    /// 1. Queue the record and wake any leader collecting a batch.
+    /// 2. If no leader is active, become the leader: wait up to `max_wait` for other
+    ///    committers (or until `max_batch_bytes` are queued), take the batch and flush it
+    ///    with `flush_batch` outside the lock.
+    /// 3. Publish the outcome of every record in the batch and wake the waiters.
+    /// 4. Return once this record's outcome has been published.
+    pub fn commit<F>(&self, entry: &LogEntry, flush_batch: F) -> StorageResult<LogSequenceNumber>
+    where
+        F: Fn(&[LogEntry]) -> StorageResult<Vec<LogSequenceNumber>>,
+    {
+        let mut state = self.state.lock().unwrap();
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        let size = entry.serialized_size();
+        state.pending.push(PendingRecord { ticket, entry: entry.clone(), size });
+        state.pending_bytes += size;
+        self.changed.notify_all();
+
+        loop {
+            if let Some(result) = state.completed.remove(&ticket) {
+                return result;
+            }
+
+            if state.leader_active {
+                state = self.changed.wait(state).unwrap();
+                continue;
+            }
+
+            state.leader_active = true;
+            state = self.wait_for_batch(state);
+            let batch = Self::take_batch(&mut state, self.config.max_batch_bytes);
+            drop(state);
+
+            let entries: Vec<LogEntry> = batch.iter().map(|record| record.entry.clone()).collect();
+            let outcome = self.flush(&entries, &flush_batch);
+
+            state = self.state.lock().unwrap();
+            let bytes: usize = batch.iter().map(|record| record.size).sum();
+            match outcome {
+                Ok(lsns) => {
+                    state.stats.batches += 1;
+                    state.stats.records += batch.len() as u64;
+                    state.stats.bytes += bytes as u64;
+                    for (record, lsn) in batch.iter().zip(lsns) {
+                        state.completed.insert(record.ticket, Ok(lsn));
+                    }
+                }
+                Err(e) => {
+                    // None of the batch is known to be durable, so every committer in it fails
+                    state.stats.failed_batches += 1;
+                    let message = format!("group commit flush failed: {}", e);
+                    for record in &batch {
+                        state.completed.insert(record.ticket, Err(StorageError::Wal(message.clone())));
+                    }
+                }
+            }
+            state.leader_active = false;
+            self.changed.notify_all();
+        }
+    }
+
+    /// Waits for more committers to join the batch, bounded by `max_wait` and `max_batch_bytes`.
+    /// A leader alone in the queue flushes straight away.
+    fn wait_for_batch<'a>(&self, mut state: MutexGuard<'a, QueueState>) -> MutexGuard<'a, QueueState> {
+        if state.pending.len() < 2 {
+            return state;
+        }
+
+        let deadline = Instant::now() + self.config.max_wait;
+        while state.pending_bytes < self.config.max_batch_bytes {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            let (guard, timeout) = self.changed.wait_timeout(state, deadline - now).unwrap();
+            state = guard;
+            if timeout.timed_out() {
+                break;
+            }
+        }
+        state
+    }
+
+    /// Takes queued records in arrival order up to `max_bytes`, always at least one
+    fn take_batch(state: &mut QueueState, max_bytes: usize) -> Vec<PendingRecord> {
+        let mut bytes = 0;
+        let count = state
+            .pending
+            .iter()
+            .take_while(|record| {
+                bytes += record.size;
+                bytes <= max_bytes
+            })
+            .count()
+            .max(1);
+        let batch: Vec<PendingRecord> = state.pending.drain(..count).collect();
+        state.pending_bytes -= batch.iter().map(|record| record.size).sum::<usize>();
+        batch
+    }
+
+    fn flush<F>(&self, entries: &[LogEntry], flush_batch: &F) -> StorageResult<Vec<LogSequenceNumber>>
+    where
+        F: Fn(&[LogEntry]) -> StorageResult<Vec<LogSequenceNumber>>,
+    {
+        #[cfg(test)]
+        if self.crash_before_flush.swap(false, std::sync::atomic::Ordering::SeqCst) {
+            return Err(StorageError::Wal("simulated crash before flush".to_string()));
+        }
+
+        let lsns = flush_batch(entries)?;
+        if lsns.len() != entries.len() {
+            return Err(StorageError::Wal(format!("flushed {} of {} records", lsns.len(), entries.len())));
+        }
+        Ok(lsns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Barrier};
+
+    fn entry(txn_id: u64) -> LogEntry {
+        LogEntry::commit_transaction(LogSequenceNumber::default(), txn_id)
+    }
+
+    fn committer(max_wait_ms: u64) -> Arc<GroupCommitter> {
+        Arc::new(GroupCommitter::new(GroupCommitConfig {
+            max_wait: Duration::from_millis(max_wait_ms),
+            max_batch_bytes: 64 * 1024,
+        }))
+    }
+
+    /// Runs `threads` concurrent commits against `flush_batch`, returning each committer's result
+    fn commit_concurrently<F>(committer: &Arc<GroupCommitter>, threads: u64, flush_batch: F) -> Vec<StorageResult<LogSequenceNumber>>
+    where
+        F: Fn(&[LogEntry]) -> StorageResult<Vec<LogSequenceNumber>> + Send + Sync + 'static,
+    {
+        let flush_batch = Arc::new(flush_batch);
+        let barrier = Arc::new(Barrier::new(threads as usize));
+        let handles: Vec<_> = (0..threads)
+            .map(|txn| {
+                let (committer, flush_batch, barrier) = (committer.clone(), flush_batch.clone(), barrier.clone());
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    committer.commit(&entry(txn), |batch| (*flush_batch)(batch))
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_lone_commit_flushes_immediately() {
+        let committer = committer(1000);
+        let started = Instant::now();
+        let lsn = committer.commit(&entry(1), |batch| Ok(batch.iter().map(|_| LogSequenceNumber { file_id: 0, offset: 7 }).collect())).unwrap();
+
+        assert_eq!(lsn.offset, 7);
+        assert!(started.elapsed() < Duration::from_millis(500), "a lone committer must not wait for max_wait");
+        let stats = committer.get_statistics();
+        assert_eq!(stats.batches, 1);
+        assert_eq!(stats.records, 1);
+    }
+
+    #[test]
+    fn test_slow_flush_batches_waiting_committers() {
+        let committer = committer(5);
+        let batch_sizes = Arc::new(Mutex::new(Vec::new()));
+        let sizes = batch_sizes.clone();
+        let results = commit_concurrently(&committer, 8, move |batch| {
+            sizes.lock().unwrap().push(batch.len());
+            // A slow fsync lets the other committers queue up behind the leader
+            std::thread::sleep(Duration::from_millis(50));
+            Ok(batch.iter().map(|e| LogSequenceNumber { file_id: 0, offset: e.transaction_id() }).collect())
+        });
+
+        for result in results {
+            result.unwrap();
+        }
+        let sizes = batch_sizes.lock().unwrap();
+        assert_eq!(sizes.iter().sum::<usize>(), 8);
+        assert!(sizes.len() < 8, "commits queued during a flush share the next fsync: {:?}", sizes);
+
+        let stats = committer.get_statistics();
+        assert_eq!(stats.batches, sizes.len() as u64);
+        assert_eq!(stats.records, 8);
+        assert!(stats.average_batch_size() > 1.0);
+    }
+
+    #[test]
+    fn test_flush_error_reaches_every_waiter() {
+        let committer = committer(5);
+        let results = commit_concurrently(&committer, 6, |_batch| {
+            std::thread::sleep(Duration::from_millis(20));
+            Err(StorageError::Wal("disk full".to_string()))
+        });
+
+        assert_eq!(results.len(), 6);
+        for result in results {
+            let err = result.unwrap_err();
+            assert!(err.to_string().contains("disk full"), "unexpected error: {}", err);
+        }
+        let stats = committer.get_statistics();
+        assert_eq!(stats.batches, 0);
+        assert!(stats.failed_batches >= 1);
+    }
+
+    #[test]
+    fn test_batch_respects_max_bytes() {
+        let size = entry(0).serialized_size();
+        let committer = Arc::new(GroupCommitter::new(GroupCommitConfig {
+            max_wait: Duration::from_millis(5),
+            max_batch_bytes: size * 2,
+        }));
+        let batch_sizes = Arc::new(Mutex::new(Vec::new()));
+        let sizes = batch_sizes.clone();
+        commit_concurrently(&committer, 6, move |batch| {
+            sizes.lock().unwrap().push(batch.len());
+            std::thread::sleep(Duration::from_millis(10));
+            Ok(batch.iter().map(|_| LogSequenceNumber::default()).collect())
+        });
+
+        assert!(batch_sizes.lock().unwrap().iter().all(|&n| n <= 2));
+    }
+}
@@ -23,13 +23,16 @@
 use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, RwLock};
 
+use serde::{Deserialize, Serialize};
+
+use crate::storage_engine::deadlock_detector::{DeadlockDetector, WaitForEdge};
 use crate::storage_engine::file_format::PageId;
 use crate::storage_engine::lib::StorageResult;
 use crate::storage_engine::mvcc::{MVCCManager, Timestamp};
 use crate::storage_engine::transaction::{IsolationLevel, TransactionId};
 
 /// Lock type for concurrency control
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum LockType {
     /// Shared lock for reading
     Shared,
@@ -69,6 +72,8 @@ pub struct LockManager {
     waiting_requests: RwLock<HashMap<PageId, Vec<LockRequest>>>,
     /// Locks held by each transaction
     transaction_locks: RwLock<HashMap<TransactionId, HashSet<PageId>>>,
+    /// Detector told about queued requests and lock counts, if any
+    deadlock_detector: Option<Arc<DeadlockDetector>>,
 }
 
 impl LockManager {
@@ -78,9 +83,16 @@ impl LockManager {
             granted_locks: RwLock::new(HashMap::new()),
             waiting_requests: RwLock::new(HashMap::new()),
             transaction_locks: RwLock::new(HashMap::new()),
+            deadlock_detector: None,
         }
     }
 
+    /// Report queued requests as wait-for edges, and lock counts, to a deadlock detector
+    pub fn with_deadlock_detector(mut self, deadlock_detector: Arc<DeadlockDetector>) -> Self {
+        self.deadlock_detector = Some(deadlock_detector);
+        self
+    }
+
     /// Request a lock on a page
     pub fn request_lock(&self, txn_id: TransactionId, page_id: PageId, lock_type: LockType) -> StorageResult<bool> {
         let mut granted_locks = self.granted_locks.write().unwrap();
@@ -100,6 +112,7 @@ impl LockManager {
 
             granted_locks.entry(page_id).or_default().push(grant);
             transaction_locks.entry(txn_id).or_default().insert(page_id);
+            self.report_locks_held(txn_id, &transaction_locks);
 
             Ok(true)
         } else {
@@ -112,10 +125,56 @@ impl LockManager {
             };
 
             waiting_requests.entry(page_id).or_default().push(request);
+
+            // The request waits on every other transaction holding a lock on the page
+            if let Some(detector) = &self.deadlock_detector {
+                for holder in current_locks.iter().map(|lock| lock.transaction_id).filter(|&holder| holder != txn_id).collect::<HashSet<_>>() {
+                    detector.add_lock_wait(WaitForEdge::new(txn_id, holder, page_id).with_lock_mode(lock_type));
+                }
+            }
             Ok(false)
         }
     }
 
+    /// Request a lock on a page without queueing
+    ///
+    /// Returns `false` if the lock conflicts with one held by another transaction. Unlike
+    /// [`request_lock`](Self::request_lock) nothing is left in the waiting queue, so callers
+    /// using no-wait locking can abort without a stale request being granted later.
+    pub fn try_lock(&self, txn_id: TransactionId, page_id: PageId, lock_type: LockType) -> StorageResult<bool> {
+        let mut granted_locks = self.granted_locks.write().unwrap();
+        let mut transaction_locks = self.transaction_locks.write().unwrap();
+
+        let current_locks = granted_locks.entry(page_id).or_default();
+        if current_locks.iter().any(|lock| lock.transaction_id == txn_id && (lock.lock_type == lock_type || lock.lock_type == LockType::Exclusive)) {
+            return Ok(true);
+        }
+        if !self.can_grant_lock(current_locks, txn_id, lock_type) {
+            if current_locks.is_empty() {
+                granted_locks.remove(&page_id);
+            }
+            return Ok(false);
+        }
+
+        current_locks.retain(|lock| lock.transaction_id != txn_id);
+        current_locks.push(LockGrant {
+            transaction_id: txn_id,
+            lock_type,
+            granted_at: crate::storage_engine::mvcc::MVCCManager::current_timestamp(),
+        });
+        transaction_locks.entry(txn_id).or_default().insert(page_id);
+        self.report_locks_held(txn_id, &transaction_locks);
+
+        Ok(true)
+    }
+
+    /// Tell the deadlock detector how many locks a transaction holds
+    fn report_locks_held(&self, txn_id: TransactionId, transaction_locks: &HashMap<TransactionId, HashSet<PageId>>) {
+        if let Some(detector) = &self.deadlock_detector {
+            detector.update_transaction_metadata(txn_id, transaction_locks.get(&txn_id).map_or(0, HashSet::len));
+        }
+    }
+
     /// Check if a lock can be granted
     fn can_grant_lock(&self, current_locks: &[LockGrant], txn_id: TransactionId, lock_type: LockType) -> bool {
         // If no current locks, can always grant
@@ -153,6 +212,15 @@ impl LockManager {
         // Get pages locked by this transaction
         let locked_pages = transaction_locks.remove(&txn_id).unwrap_or_default();
 
+        // A finished transaction's queued requests must never be granted
+        waiting_requests.retain(|_, requests| {
+            requests.retain(|request| request.transaction_id != txn_id);
+            !requests.is_empty()
+        });
+        if let Some(detector) = &self.deadlock_detector {
+            detector.remove_transaction(txn_id);
+        }
+
         // Release locks on each page
         for page_id in locked_pages {
             if let Some(locks) = granted_locks.get_mut(&page_id) {
@@ -195,6 +263,10 @@ impl LockManager {
 
                     granted_locks.entry(page_id).or_default().push(grant);
                     transaction_locks.entry(request.transaction_id).or_default().insert(page_id);
+                    if let Some(detector) = &self.deadlock_detector {
+                        detector.remove_lock_waits(request.transaction_id, page_id);
+                        detector.update_transaction_metadata(request.transaction_id, transaction_locks[&request.transaction_id].len());
+                    }
                     granted_any = true;
                     false // Remove from waiting queue
                 } else {
@@ -519,6 +591,29 @@ mod tests {
         assert!(lock_manager.holds_lock(txn_id, page_id, LockType::Exclusive));
     }
 
+    #[test]
+    fn test_try_lock_does_not_queue() {
+        let lock_manager = LockManager::new();
+        let page_id = PageId(1);
+
+        assert!(lock_manager.try_lock(1, page_id, LockType::Shared).unwrap());
+        assert!(lock_manager.try_lock(2, page_id, LockType::Shared).unwrap());
+
+        // Upgrading is refused while another reader holds the page
+        assert!(!lock_manager.try_lock(1, page_id, LockType::Exclusive).unwrap());
+        assert!(lock_manager.get_waiting_transactions().is_empty());
+
+        // Once the other reader is gone the upgrade replaces the shared grant
+        lock_manager.release_transaction_locks(2).unwrap();
+        assert!(lock_manager.try_lock(1, page_id, LockType::Exclusive).unwrap());
+        assert!(lock_manager.holds_lock(1, page_id, LockType::Exclusive));
+        assert_eq!(lock_manager.get_statistics().total_granted_locks, 1);
+
+        assert!(!lock_manager.try_lock(3, page_id, LockType::Shared).unwrap());
+        lock_manager.release_transaction_locks(1).unwrap();
+        assert!(!lock_manager.holds_lock(3, page_id, LockType::Shared));
+    }
+
     #[test]
     fn test_isolation_level_enforcer() {
         let mvcc = Arc::new(MVCCManager::new());
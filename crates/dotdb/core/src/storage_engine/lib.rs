@@ -23,8 +23,11 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::storage_engine::deadlock_detector::DeadlockResolutionPolicy;
+use crate::storage_engine::eviction::ReplacementPolicy;
 // Forward declaration for use in Storage trait
 use crate::storage_engine::file_format::Page;
+use crate::storage_engine::wal::LogSequenceNumber;
 
 /// Represents a unique identifier for a database instance
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -49,8 +52,12 @@ pub struct StorageConfig {
     pub page_size: usize,
     /// Maximum number of pages to keep in the buffer pool
     pub buffer_pool_size: usize,
+    /// Eviction policy used by the buffer pool
+    pub eviction_policy: ReplacementPolicy,
     /// Whether to use direct I/O (bypassing filesystem cache)
     pub direct_io: bool,
+    /// How pages are read and written
+    pub io_backend: IoBackend,
     /// Size of the WAL in bytes
     pub wal_size: usize,
     /// Flush interval in milliseconds
@@ -59,6 +66,12 @@ pub struct StorageConfig {
     pub max_dirty_pages: usize,
     /// Background writer thread count
     pub writer_threads: usize,
+    /// Background compaction thread count, `None` to use `writer_threads`
+    pub compaction_threads: Option<usize>,
+    /// IO budget for background compaction in bytes per second, 0 for unlimited
+    pub compaction_io_budget_bytes_per_sec: u64,
+    /// Which transaction of a deadlock cycle to abort
+    pub deadlock_policy: DeadlockResolutionPolicy,
 }
 
 impl Default for StorageConfig {
@@ -67,11 +80,16 @@ impl Default for StorageConfig {
             path: PathBuf::from("./data"),
             page_size: 4096,
             buffer_pool_size: 10000,
+            eviction_policy: ReplacementPolicy::LRU,
             direct_io: false,
+            io_backend: IoBackend::Pread,
             wal_size: 64 * 1024 * 1024, // 64 MB
             flush_interval_ms: 1000,
             max_dirty_pages: 1000,
             writer_threads: 2,
+            compaction_threads: None,
+            compaction_io_budget_bytes_per_sec: 20 * 1024 * 1024, // 20 MB/s
+            deadlock_policy: DeadlockResolutionPolicy::AbortYoungest,
         }
     }
 }
@@ -103,14 +121,41 @@ pub enum StorageError {
     #[error("WAL error: {0}")]
     Wal(String),
 
+    #[error("Unrecoverable WAL range {from}..{to}: {reason}")]
+    UnrecoverableWal { from: LogSequenceNumber, to: LogSequenceNumber, reason: String },
+
     #[error("Concurrency error: {0}")]
     Concurrency(String),
 
+    #[error("Deadlock: transaction {victim} aborted to break wait-for cycle {} on pages {resources:?}", cycle_path(.cycle))]
+    Deadlock { victim: u64, cycle: Vec<u64>, resources: Vec<u64> },
+
     #[error("Not found: {0}")]
     NotFound(String),
 
     #[error("Invalid operation: {0}")]
     InvalidOperation(String),
+
+    #[error("{setting} is only read at startup; change it in the node's configuration and restart the node")]
+    RestartRequired { setting: String },
+
+    #[error("Invalid value for {setting}: {reason}")]
+    InvalidConfig { setting: String, reason: String },
+
+    #[error("{} is encrypted; supply its master key through DOTDB_MASTER_KEY, DOTDB_MASTER_KEY_FILE or a key provider", .path.display())]
+    EncryptionKeyRequired { path: PathBuf },
+
+    #[error("Encryption error: {0}")]
+    Encryption(String),
+}
+
+/// Render a wait-for cycle as `1 -> 2 -> 3 -> 1`
+fn cycle_path(cycle: &[u64]) -> String {
+    let mut path: Vec<String> = cycle.iter().map(u64::to_string).collect();
+    if let Some(first) = path.first().cloned() {
+        path.push(first);
+    }
+    path.join(" -> ")
 }
 
 /// Result type for storage operations
@@ -142,6 +187,17 @@ pub enum StorageDevice {
     BlockDevice,
 }
 
+/// How pages are read and written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IoBackend {
+    /// Positional reads and writes, one system call per page
+    #[default]
+    Pread,
+    /// io_uring with up to `queue_depth` requests in flight. Needs the `io-uring` feature
+    /// on Linux; elsewhere, or if the kernel refuses to set up a ring, `Pread` is used.
+    IoUring { queue_depth: u32 },
+}
+
 /// Safely open a file or block device for storage
 pub fn open_storage<P: AsRef<Path>>(path: P, device_type: StorageDevice, create: bool, direct_io: bool) -> StorageResult<File> {
     let mut options = OpenOptions::new();
@@ -199,6 +255,23 @@ pub trait AsyncIO: Send + Sync {
     fn read_page(&self, page_id: u64, buffer: &mut [u8]) -> StorageResult<usize>;
     fn write_page(&self, page_id: u64, buffer: &[u8]) -> StorageResult<usize>;
     fn sync(&self) -> StorageResult<()>;
+
+    /// Read several pages, each into its own buffer. Implementations may have the reads
+    /// in flight at once; by default they run one after another.
+    fn read_pages(&self, requests: &mut [(u64, &mut [u8])]) -> StorageResult<()> {
+        for (page_id, buffer) in requests.iter_mut() {
+            self.read_page(*page_id, buffer)?;
+        }
+        Ok(())
+    }
+
+    /// Write several pages; like [`read_pages`](Self::read_pages), possibly at once
+    fn write_pages(&self, requests: &[(u64, &[u8])]) -> StorageResult<()> {
+        for (page_id, buffer) in requests {
+            self.write_page(*page_id, buffer)?;
+        }
+        Ok(())
+    }
 }
 
 /// A trait defining storage operations
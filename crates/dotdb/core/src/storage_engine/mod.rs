@@ -19,23 +19,46 @@
 
 pub mod buffer_manager;
 pub mod deadlock_detector;
+pub mod encryption;
+pub mod engine;
+pub mod eviction;
+pub(crate) mod failpoint;
 pub mod file_format;
+pub mod group_commit;
 pub mod isolation;
 pub mod lib;
 pub mod mvcc;
 pub mod occ;
 pub mod page_manager;
 pub mod transaction;
+pub mod vacuum;
 pub mod wal;
+pub mod wal_archive;
+pub mod writeback;
 
 // Public exports
-pub use buffer_manager::{Buffer, BufferManager, BufferPool, BufferStats};
-pub use deadlock_detector::{DeadlockCycle, DeadlockDetector, DeadlockResolutionPolicy, DeadlockStatistics, WaitForEdge};
-pub use file_format::{FileFormat, Page, PageId, PageType};
+pub use buffer_manager::{Buffer, BufferManager, BufferPool, BufferPoolStats, BufferStats};
+pub use deadlock_detector::{
+    DeadlockCycle, DeadlockDetectionService, DeadlockDetector, DeadlockResolutionPolicy, DeadlockStatistics, DeadlockVictim, LOCKS_STATUS_FILE, TransactionLockInfo, VictimSelector, WaitEdgeInfo, WaitForEdge,
+    WaitForGraphSnapshot,
+};
+pub use encryption::{
+    CallbackKeyProvider, DataKey, EncryptionHeader, EncryptionStatus, KeyProvider, MASTER_KEY_ENV, MASTER_KEY_FILE_ENV, MasterKey, ReencryptionBatch, ReencryptionConfig, ReencryptionService,
+    RotationProgress, WrappedKey,
+};
+pub use engine::{AppliedConfigChange, StorageConfigDelta, StorageEngine};
+pub use eviction::{AccessHint, ClockPolicy, EvictionPolicy, FifoPolicy, LruKPolicy, LruPolicy, MruPolicy, ReplacementPolicy};
+#[cfg(feature = "failpoints")]
+pub use failpoint::{Failpoint, FailpointAction, FailpointPolicy, FaultInjector, TriggerAt};
+pub use file_format::{CorruptPage, FileFormat, Page, PageId, PageType, VerifyReport};
+pub use group_commit::{GroupCommitConfig, GroupCommitStats, GroupCommitter};
 pub use isolation::{IsolationLevelEnforcer, IsolationStatistics, LockManager, LockStatistics, LockType};
-pub use lib::{AsyncIO, DatabaseId, Flushable, Initializable, Storage, StorageConfig, StorageDevice, StorageError, StorageResult, VersionId, calculate_checksum, generate_timestamp};
-pub use mvcc::{MVCCManager, MVCCStatistics, TransactionSnapshot, VersionInfo};
+pub use lib::{AsyncIO, DatabaseId, Flushable, Initializable, IoBackend, Storage, StorageConfig, StorageDevice, StorageError, StorageResult, VersionId, calculate_checksum, generate_timestamp};
+pub use mvcc::{GcHorizon, MVCCManager, MVCCStatistics, RetentionPolicy, TransactionSnapshot, VersionInfo};
 pub use occ::{ConflictResolution, ConflictResolutionStrategy, ConflictType, OCCManager, OCCStatistics, OCCTransaction, OCCTransactionManager, ValidationContext};
 pub use page_manager::{PageAllocation, PageManager};
 pub use transaction::{IsolationLevel, Transaction, TransactionManager, TransactionState};
-pub use wal::{LogEntry, LogSequenceNumber, WriteAheadLog};
+pub use vacuum::{VACUUM_REQUESTS_FILE, VACUUM_STATUS_FILE, Vacuum, VacuumConfig, VacuumRunReport, VacuumService, VacuumStatus, request_collection_vacuum};
+pub use wal::{LogEntry, LogSequenceNumber, RecordType, WalConfig, WriteAheadLog};
+pub use wal_archive::{ArchiveCompression, ArchivedSegment, RecoveryReport, RecoveryTarget, WalArchive, WalArchiveConfig};
+pub use writeback::{Backpressure, FLUSH_HARD_LIMIT_RATIO, FLUSH_START_RATIO, MAX_COMMIT_DELAY, WritebackQueue, WriterThroughput};
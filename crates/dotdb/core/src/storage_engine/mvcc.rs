@@ -18,7 +18,7 @@
 
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::{Arc, Mutex, RwLock};
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use crate::state::dot_storage_layout::DotAddress;
 use crate::state::mpt::Hash;
@@ -102,6 +102,40 @@ impl VersionInfo {
     }
 }
 
+/// How much history garbage collection keeps beyond what active transactions need
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Committed versions kept per page in addition to the latest, e.g. for state diffs
+    pub keep_history: usize,
+    /// Versions younger than this are never collected
+    pub retain_for: Duration,
+}
+
+/// Bounds on which versions a garbage collection pass may remove
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GcHorizon {
+    /// Timestamp of the oldest active snapshot; a version superseded at or before it is invisible to every transaction
+    pub watermark: Timestamp,
+    /// Only versions created before this timestamp may be removed
+    pub retention_cutoff: Timestamp,
+    /// Committed versions kept per chain in addition to the latest
+    pub keep_history: usize,
+    /// Timestamps of named snapshots, whose visible versions are always kept
+    pub pinned: Vec<Timestamp>,
+}
+
+impl GcHorizon {
+    /// Horizon that only respects the given watermark
+    pub fn at(watermark: Timestamp) -> Self {
+        Self {
+            watermark,
+            retention_cutoff: Timestamp::MAX,
+            keep_history: 0,
+            pinned: Vec::new(),
+        }
+    }
+}
+
 /// Version chain for a single data item
 #[derive(Debug, Default)]
 pub struct VersionChain {
@@ -157,7 +191,7 @@ impl VersionChain {
     pub fn commit_version(&mut self, timestamp: Timestamp) -> StorageResult<()> {
         if let Some(version) = self.versions.get_mut(&timestamp) {
             version.commit();
-            self.latest_committed = Some(timestamp);
+            self.latest_committed = self.latest_committed.max(Some(timestamp));
             Ok(())
         } else {
             Err(StorageError::Corruption("Version not found for commit".to_string()))
@@ -166,28 +200,31 @@ impl VersionChain {
 
     /// Remove old versions that are no longer needed
     pub fn garbage_collect(&mut self, oldest_active_timestamp: Timestamp) -> Vec<VersionInfo> {
-        let mut removed_versions = Vec::new();
-        let mut to_remove = Vec::new();
+        self.collect_garbage(&GcHorizon::at(oldest_active_timestamp), usize::MAX)
+    }
 
-        for (&timestamp, version) in &self.versions {
-            // Can only remove versions that are older than oldest active transaction
-            if timestamp < oldest_active_timestamp && version.is_committed {
-                // Check if there's a newer committed version
-                if let Some(latest) = self.latest_committed
-                    && latest > timestamp
-                {
-                    to_remove.push(timestamp);
-                }
-            }
-        }
+    /// Remove up to `limit` committed versions that fall behind the horizon, oldest first
+    ///
+    /// A version is removable once a newer committed version is visible to every active
+    /// snapshot, it is older than the retention cutoff, enough newer history remains, and
+    /// no named snapshot sees it. Uncommitted versions are never removed.
+    pub fn collect_garbage(&mut self, horizon: &GcHorizon, limit: usize) -> Vec<VersionInfo> {
+        let committed: Vec<Timestamp> = self.versions.iter().filter(|(_, version)| version.is_committed).map(|(&timestamp, _)| timestamp).collect();
 
-        for timestamp in to_remove {
-            if let Some(version) = self.versions.remove(&timestamp) {
-                removed_versions.push(version);
+        let mut to_remove = Vec::new();
+        for (index, pair) in committed.windows(2).enumerate() {
+            if to_remove.len() >= limit {
+                break;
+            }
+            let (timestamp, successor) = (pair[0], pair[1]);
+            let newer_versions = committed.len() - 1 - index;
+            let pinned = horizon.pinned.iter().any(|&pin| timestamp <= pin && pin < successor);
+            if successor <= horizon.watermark && timestamp < horizon.retention_cutoff && newer_versions > horizon.keep_history && !pinned {
+                to_remove.push(timestamp);
             }
         }
 
-        removed_versions
+        to_remove.into_iter().filter_map(|timestamp| self.versions.remove(&timestamp)).collect()
     }
 
     /// Get the number of versions in the chain
@@ -249,6 +286,12 @@ pub struct MVCCManager {
     timestamp_counter: Mutex<Timestamp>,
     /// Garbage collection threshold
     gc_threshold: usize,
+    /// History kept by garbage collection
+    retention: RwLock<RetentionPolicy>,
+    /// Named snapshots and the timestamp each one reads at
+    named_snapshots: RwLock<BTreeMap<String, Timestamp>>,
+    /// Collection each page belongs to, for scoped vacuums
+    page_collections: RwLock<HashMap<PageId, String>>,
     /// Dot version manager for dot state versioning integration
     dot_version_manager: Arc<DotVersionManager>,
     /// Transaction to dot state mapping
@@ -266,6 +309,9 @@ impl MVCCManager {
             gc_threshold: 1000, // Trigger GC after 1000 versions
             dot_version_manager: Arc::new(DotVersionManager::default()),
             transaction_dot_states: RwLock::new(HashMap::new()),
+            retention: RwLock::new(RetentionPolicy::default()),
+            named_snapshots: RwLock::new(BTreeMap::new()),
+            page_collections: RwLock::new(HashMap::new()),
         }
     }
 
@@ -277,6 +323,9 @@ impl MVCCManager {
             commit_timestamps: RwLock::new(HashMap::new()),
             timestamp_counter: Mutex::new(Self::current_timestamp()),
             gc_threshold: 1000,
+            retention: RwLock::new(RetentionPolicy::default()),
+            named_snapshots: RwLock::new(BTreeMap::new()),
+            page_collections: RwLock::new(HashMap::new()),
             dot_version_manager: dot_manager,
             transaction_dot_states: RwLock::new(HashMap::new()),
         }
@@ -313,12 +362,15 @@ impl MVCCManager {
         let timestamp = self.next_timestamp();
         let version = VersionInfo::new(data, txn_id, timestamp);
 
-        let mut chains = self.version_chains.write().unwrap();
-        let chain = chains.entry(page_id).or_default();
-        chain.add_version(version);
+        let needs_gc = {
+            let mut chains = self.version_chains.write().unwrap();
+            let chain = chains.entry(page_id).or_default();
+            chain.add_version(version);
+            chain.version_count() > self.gc_threshold
+        };
 
-        // Trigger garbage collection if needed
-        if chain.version_count() > self.gc_threshold {
+        // Trigger garbage collection if needed, after releasing the chains lock it takes
+        if needs_gc {
             self.trigger_garbage_collection(page_id)?;
         }
 
@@ -353,9 +405,7 @@ impl MVCCManager {
             let timestamps: Vec<Timestamp> = chain.get_versions_by_transaction(txn_id).iter().map(|v| v.created_at).collect();
 
             for timestamp in timestamps {
-                if let Some(version_mut) = chain.versions.get_mut(&timestamp) {
-                    version_mut.commit();
-                }
+                chain.commit_version(timestamp)?;
             }
         }
 
@@ -385,23 +435,92 @@ impl MVCCManager {
 
     /// Trigger garbage collection for a specific page
     fn trigger_garbage_collection(&self, page_id: PageId) -> StorageResult<()> {
-        let oldest_active_timestamp = self.get_oldest_active_timestamp();
-
-        let mut chains = self.version_chains.write().unwrap();
-        if let Some(chain) = chains.get_mut(&page_id) {
-            let _removed = chain.garbage_collect(oldest_active_timestamp);
-            // Log or handle removed versions if needed
-        }
-
+        let horizon = self.gc_horizon();
+        self.vacuum_page(page_id, &horizon, usize::MAX);
         Ok(())
     }
 
-    /// Get the oldest active transaction timestamp
-    fn get_oldest_active_timestamp(&self) -> Timestamp {
+    /// Get the oldest active transaction timestamp, the watermark garbage collection stays behind
+    pub fn oldest_active_timestamp(&self) -> Timestamp {
         let snapshots = self.transaction_snapshots.read().unwrap();
         snapshots.values().map(|s| s.timestamp).min().unwrap_or_else(|| self.next_timestamp())
     }
 
+    /// Set how much history garbage collection keeps
+    pub fn set_retention_policy(&self, policy: RetentionPolicy) {
+        *self.retention.write().unwrap() = policy;
+    }
+
+    /// Get the history garbage collection keeps
+    pub fn retention_policy(&self) -> RetentionPolicy {
+        *self.retention.read().unwrap()
+    }
+
+    /// Pin the current state under a name so garbage collection keeps every version it sees
+    ///
+    /// Returns the timestamp the snapshot reads at. Pinning an existing name moves it forward.
+    pub fn pin_snapshot(&self, name: &str) -> Timestamp {
+        let timestamp = self.next_timestamp();
+        self.named_snapshots.write().unwrap().insert(name.to_string(), timestamp);
+        timestamp
+    }
+
+    /// Release a named snapshot, returning whether it existed
+    pub fn release_snapshot(&self, name: &str) -> bool {
+        self.named_snapshots.write().unwrap().remove(name).is_some()
+    }
+
+    /// Get the named snapshots and the timestamps they read at
+    pub fn named_snapshots(&self) -> Vec<(String, Timestamp)> {
+        self.named_snapshots.read().unwrap().iter().map(|(name, &timestamp)| (name.clone(), timestamp)).collect()
+    }
+
+    /// Record that a page belongs to a collection
+    pub fn assign_page(&self, page_id: PageId, collection: &str) {
+        self.page_collections.write().unwrap().insert(page_id, collection.to_string());
+    }
+
+    /// Get the versioned pages of a collection, in page order
+    pub fn collection_pages(&self, collection: &str) -> Vec<PageId> {
+        let owners = self.page_collections.read().unwrap();
+        let chains = self.version_chains.read().unwrap();
+        let mut pages: Vec<PageId> = chains.keys().filter(|page_id| owners.get(page_id).is_some_and(|owner| owner == collection)).copied().collect();
+        pages.sort_by_key(|page_id| page_id.0);
+        pages
+    }
+
+    /// Get every page with a version chain, in page order
+    pub fn versioned_pages(&self) -> Vec<PageId> {
+        let mut pages: Vec<PageId> = self.version_chains.read().unwrap().keys().copied().collect();
+        pages.sort_by_key(|page_id| page_id.0);
+        pages
+    }
+
+    /// Compute what garbage collection may remove right now
+    pub fn gc_horizon(&self) -> GcHorizon {
+        let retention = self.retention_policy();
+        let retention_cutoff = if retention.retain_for.is_zero() {
+            Timestamp::MAX
+        } else {
+            Self::current_timestamp().saturating_sub(retention.retain_for.as_nanos() as Timestamp)
+        };
+
+        GcHorizon {
+            watermark: self.oldest_active_timestamp(),
+            retention_cutoff,
+            keep_history: retention.keep_history,
+            pinned: self.named_snapshots.read().unwrap().values().copied().collect(),
+        }
+    }
+
+    /// Remove up to `limit` collectable versions of one page, returning them
+    ///
+    /// The chains lock is held for this page only, so callers can vacuum incrementally.
+    pub fn vacuum_page(&self, page_id: PageId, horizon: &GcHorizon, limit: usize) -> Vec<VersionInfo> {
+        let mut chains = self.version_chains.write().unwrap();
+        chains.get_mut(&page_id).map(|chain| chain.collect_garbage(horizon, limit)).unwrap_or_default()
+    }
+
     /// Get transaction commit timestamp
     pub fn get_commit_timestamp(&self, txn_id: TransactionId) -> Option<Timestamp> {
         self.commit_timestamps.read().unwrap().get(&txn_id).copied()
@@ -686,6 +805,85 @@ mod tests {
         assert!(stats_after.total_versions <= stats_before.total_versions);
     }
 
+    fn write_committed_versions(mvcc: &MVCCManager, page_id: PageId, txns: std::ops::RangeInclusive<TransactionId>) {
+        for txn_id in txns {
+            mvcc.create_snapshot(txn_id, IsolationLevel::ReadCommitted).unwrap();
+            mvcc.add_version(page_id, create_test_page(&txn_id.to_le_bytes()), txn_id).unwrap();
+            mvcc.commit_transaction(txn_id).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_gc_stays_behind_oldest_active_snapshot() {
+        let mvcc = MVCCManager::new();
+        let page_id = PageId(1);
+        write_committed_versions(&mvcc, page_id, 1..=2);
+
+        // A reader started here still needs the second version once a third is written
+        mvcc.create_snapshot(100, IsolationLevel::RepeatableRead).unwrap();
+        write_committed_versions(&mvcc, page_id, 3..=3);
+
+        let removed = mvcc.vacuum_page(page_id, &mvcc.gc_horizon(), usize::MAX);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].created_by, 1);
+        assert!(mvcc.get_visible_version(page_id, 100).unwrap().is_some());
+
+        mvcc.commit_transaction(100).unwrap();
+        let removed = mvcc.vacuum_page(page_id, &mvcc.gc_horizon(), usize::MAX);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].created_by, 2);
+        assert_eq!(mvcc.get_statistics().total_versions, 1);
+    }
+
+    #[test]
+    fn test_gc_keeps_retained_history() {
+        let mvcc = MVCCManager::new();
+        let page_id = PageId(1);
+        write_committed_versions(&mvcc, page_id, 1..=5);
+
+        mvcc.set_retention_policy(RetentionPolicy {
+            keep_history: 2,
+            retain_for: Duration::ZERO,
+        });
+        let removed = mvcc.vacuum_page(page_id, &mvcc.gc_horizon(), usize::MAX);
+        assert_eq!(removed.iter().map(|v| v.created_by).collect::<Vec<_>>(), vec![1, 2]);
+
+        // Nothing is old enough under a time-based window
+        mvcc.set_retention_policy(RetentionPolicy {
+            keep_history: 0,
+            retain_for: Duration::from_secs(3600),
+        });
+        assert!(mvcc.vacuum_page(page_id, &mvcc.gc_horizon(), usize::MAX).is_empty());
+    }
+
+    #[test]
+    fn test_gc_never_removes_versions_of_named_snapshots() {
+        let mvcc = MVCCManager::new();
+        let page_id = PageId(1);
+        write_committed_versions(&mvcc, page_id, 1..=2);
+        mvcc.pin_snapshot("before-migration");
+        write_committed_versions(&mvcc, page_id, 3..=4);
+
+        let removed = mvcc.vacuum_page(page_id, &mvcc.gc_horizon(), usize::MAX);
+        assert_eq!(removed.iter().map(|v| v.created_by).collect::<Vec<_>>(), vec![1, 3]);
+
+        assert!(mvcc.release_snapshot("before-migration"));
+        let removed = mvcc.vacuum_page(page_id, &mvcc.gc_horizon(), usize::MAX);
+        assert_eq!(removed.iter().map(|v| v.created_by).collect::<Vec<_>>(), vec![2]);
+    }
+
+    #[test]
+    fn test_gc_removes_at_most_limit_versions() {
+        let mvcc = MVCCManager::new();
+        let page_id = PageId(1);
+        write_committed_versions(&mvcc, page_id, 1..=5);
+
+        let horizon = mvcc.gc_horizon();
+        assert_eq!(mvcc.vacuum_page(page_id, &horizon, 3).len(), 3);
+        assert_eq!(mvcc.vacuum_page(page_id, &horizon, 3).len(), 1);
+        assert_eq!(mvcc.get_statistics().total_versions, 1);
+    }
+
     #[test]
     fn test_transaction_abort() {
         let mvcc = MVCCManager::new();
@@ -792,6 +792,8 @@ mod tests {
             directory: temp_dir.path().to_path_buf(),
             max_file_size: 1024 * 1024,
             direct_io: false,
+            archive: None,
+            group_commit: None,
         };
         Arc::new(WriteAheadLog::new(wal_config).unwrap())
     }
@@ -20,7 +20,7 @@
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::{Arc, Mutex, RwLock};
 
-use crate::storage_engine::file_format::{FileFormat, PageId, PageType};
+use crate::storage_engine::file_format::{FileFormat, Page, PageHeader, PageId, PageType};
 use crate::storage_engine::lib::{Initializable, StorageError, StorageResult, VersionId};
 
 /// Result of a page allocation operation
@@ -288,6 +288,32 @@ impl PageManager {
         Ok(free_count)
     }
 
+    /// Number of data bytes each allocated page can hold
+    pub fn page_capacity(&self) -> StorageResult<usize> {
+        let file_format = self.file_format.lock().map_err(|_| StorageError::Corruption("Failed to lock file format".to_string()))?;
+        Ok(file_format.page_capacity())
+    }
+
+    /// Write data to an allocated page, tagging it with the allocation's page type
+    pub fn write_page_data(&mut self, allocation: &PageAllocation, data: &[u8]) -> StorageResult<()> {
+        let mut file_format = self.file_format.lock().map_err(|_| StorageError::Corruption("Failed to lock file format".to_string()))?;
+
+        let capacity = file_format.page_capacity();
+        if data.len() > capacity {
+            return Err(StorageError::InvalidOperation(format!("{} bytes do not fit in a page of {capacity} bytes", data.len())));
+        }
+
+        let mut page = Page::new(allocation.page_id, allocation.page_type, allocation.version, capacity + PageHeader::size());
+        page.data[..data.len()].copy_from_slice(data);
+        file_format.write_page(&mut page)
+    }
+
+    /// Read the data stored in a page
+    pub fn read_page_data(&self, page_id: PageId) -> StorageResult<Vec<u8>> {
+        let mut file_format = self.file_format.lock().map_err(|_| StorageError::Corruption("Failed to lock file format".to_string()))?;
+        Ok(file_format.read_page(page_id)?.data)
+    }
+
     /// Get a specific version of a page
     pub fn get_page_version(&self, page_id: PageId, version: VersionId) -> StorageResult<Option<VersionId>> {
         if let Some(versions) = self.page_versions.get(&page_id) {
@@ -466,6 +492,7 @@ impl ConcurrentPageManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage_engine::eviction::ReplacementPolicy;
     use crate::storage_engine::file_format::FileFormat;
     use crate::storage_engine::lib::StorageConfig;
     use std::sync::{Arc, Mutex};
@@ -478,11 +505,16 @@ mod tests {
             path: file_path,
             page_size: 4096,
             buffer_pool_size: 100,
+            eviction_policy: ReplacementPolicy::LRU,
             direct_io: false,
+            io_backend: Default::default(),
             wal_size: 1024 * 1024,
             flush_interval_ms: 100,
             max_dirty_pages: 10,
             writer_threads: 1,
+            compaction_threads: None,
+            compaction_io_budget_bytes_per_sec: 0,
+            deadlock_policy: Default::default(),
         };
         let mut file_format = FileFormat::new(config);
         file_format.init().unwrap();
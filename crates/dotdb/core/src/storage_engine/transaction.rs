@@ -21,11 +21,11 @@ use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Condvar, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::time::{Duration, Instant};
 
-use crate::storage_engine::buffer_manager::BufferManager;
-use crate::storage_engine::deadlock_detector::DeadlockDetector;
+use crate::storage_engine::buffer_manager::{AccessHint, BufferManager};
+use crate::storage_engine::deadlock_detector::{DeadlockDetector, WaitForGraphSnapshot};
 use crate::storage_engine::file_format::{Page, PageId, PageType};
 use crate::storage_engine::isolation::{IsolationLevelEnforcer, LockManager};
-use crate::storage_engine::lib::{StorageError, StorageResult, VersionId, generate_timestamp};
+use crate::storage_engine::lib::{StorageConfig, StorageError, StorageResult, VersionId, generate_timestamp};
 use crate::storage_engine::mvcc::MVCCManager;
 use crate::storage_engine::occ::{ConflictResolution, ConflictResolutionStrategy, OCCManager, OCCTransaction, OCCTransactionManager, ValidationContext};
 use crate::storage_engine::wal::{LogEntry, LogSequenceNumber, WriteAheadLog};
@@ -189,12 +189,27 @@ impl Transaction {
 
     /// Read a page
     pub fn read_page(&mut self, page_id: PageId) -> StorageResult<Arc<Page>> {
+        self.read_page_with_hint(page_id, AccessHint::Normal)
+    }
+
+    /// Read a page as part of a collection or full-table scan
+    ///
+    /// Pages the scan brings into the buffer pool are evicted before the working set.
+    pub fn scan_page(&mut self, page_id: PageId) -> StorageResult<Arc<Page>> {
+        self.read_page_with_hint(page_id, AccessHint::UseOnce)
+    }
+
+    fn read_page_with_hint(&mut self, page_id: PageId, hint: AccessHint) -> StorageResult<Arc<Page>> {
         if self.state != TransactionState::Active {
             return Err(StorageError::TransactionAborted(format!("Cannot read page in transaction state: {:?}", self.state)));
         }
 
+        // A deadlock victim must abort instead of waiting on more locks
+        self.deadlock_detector.check_victim(self.id)?;
+
         // Check isolation level constraints before reading
         if !self.isolation_enforcer.check_read(self.id, page_id, self.isolation_level)? {
+            self.check_deadlock()?;
             return Err(StorageError::Concurrency("Read blocked due to isolation level constraints".to_string()));
         }
 
@@ -229,7 +244,10 @@ impl Transaction {
         }
 
         // Get the page from the buffer pool
-        self.buffer_manager.get_page(page_id)
+        match hint {
+            AccessHint::Normal => self.buffer_manager.get_page(page_id),
+            AccessHint::UseOnce => self.buffer_manager.get_page_for_scan(page_id),
+        }
     }
 
     /// Write a page
@@ -238,8 +256,12 @@ impl Transaction {
             return Err(StorageError::TransactionAborted(format!("Cannot write page in transaction state: {:?}", self.state)));
         }
 
+        // A deadlock victim must abort instead of waiting on more locks
+        self.deadlock_detector.check_victim(self.id)?;
+
         // Check isolation level constraints before writing
         if !self.isolation_enforcer.check_write(self.id, page_id, self.isolation_level)? {
+            self.check_deadlock()?;
             return Err(StorageError::Concurrency("Write blocked due to isolation level constraints or deadlock".to_string()));
         }
 
@@ -248,6 +270,7 @@ impl Transaction {
 
         // Before image for rollback
         let before_image = page_guard.page().data.clone();
+        let bytes_written = data.len() as u64;
 
         // Update the page data
         page_guard.update(data)?;
@@ -258,8 +281,8 @@ impl Transaction {
         // Add version to MVCC manager
         self.mvcc_manager.add_version(page_id, page.clone(), self.id)?;
 
-        // Update deadlock detector with resource held information
-        self.deadlock_detector.update_transaction_metadata(self.id, self.write_set.len() + 1);
+        // Track work done for the least-work deadlock victim policy
+        self.deadlock_detector.record_write(self.id, bytes_written);
 
         // Log the write operation using the complete page
         let next_lsn = self.wal.next_lsn()?;
@@ -355,19 +378,36 @@ impl Transaction {
         self.mvcc_manager.get_dot_state_at_snapshot(self.id, dot_address)
     }
 
+    /// Run deadlock detection after a blocked lock request, which may have closed a
+    /// wait-for cycle, and fail with the cycle if this transaction was chosen to break it
+    fn check_deadlock(&self) -> StorageResult<()> {
+        self.deadlock_detector.detect_and_resolve_deadlocks()?;
+        self.deadlock_detector.check_victim(self.id)
+    }
+
     /// Commit this transaction
     ///
     /// Steps:
-    /// 1. Change state to Committing and set commit timestamp.
-    /// 2. Write a commit record to the WAL and flush for durability.
-    /// 3. Commit in MVCC manager and release locks.
-    /// 4. Change state to Committed and update last LSN.
-    /// 5. Return the new version (base_version + 1).
+    /// 1. Wait out the dirty page backpressure of the buffer pool.
+    /// 2. Change state to Committing and set commit timestamp.
+    /// 3. Write a commit record to the WAL and flush for durability.
+    /// 4. Commit in MVCC manager and release locks.
+    /// 5. Change state to Committed and update last LSN.
+    /// 6. Return the new version (base_version + 1).
+    ///
+    /// Dirty pages are written back by the buffer pool's background writers; the commit
+    /// only waits for its WAL record to be durable.
     pub fn commit(&mut self) -> StorageResult<VersionId> {
         if self.state != TransactionState::Active {
             return Err(StorageError::TransactionAborted(format!("Cannot commit transaction in state: {:?}", self.state)));
         }
 
+        // A deadlock victim can only abort
+        self.deadlock_detector.check_victim(self.id)?;
+
+        // Slow down while the background writers catch up with the dirty pages
+        self.buffer_manager.throttle_commit()?;
+
         // Update the state
         self.state = TransactionState::Committing;
 
@@ -380,11 +420,8 @@ impl Transaction {
         // Create a commit transaction record
         let commit_record = LogEntry::commit_transaction(next_lsn, self.id);
 
-        // Append to the WAL
-        self.wal.append(&commit_record)?;
-
-        // Flush the WAL to ensure durability
-        self.wal.flush()?;
+        // Append to the WAL and wait until it is durable; concurrent commits share one fsync
+        self.wal.append_durable(&commit_record)?;
 
         // Commit in isolation enforcer (handles MVCC commit and lock release)
         self.isolation_enforcer.handle_commit(self.id)?;
@@ -429,11 +466,8 @@ impl Transaction {
         // Create an abort transaction record
         let abort_record = LogEntry::abort_transaction(next_lsn, self.id);
 
-        // Append to the WAL
-        self.wal.append(&abort_record)?;
-
-        // Flush the WAL
-        self.wal.flush()?;
+        // Append to the WAL and wait until it is durable
+        self.wal.append_durable(&abort_record)?;
 
         // Abort in isolation enforcer (handles MVCC abort and lock release)
         self.isolation_enforcer.handle_abort(self.id)?;
@@ -441,8 +475,9 @@ impl Transaction {
         // Rollback dot state changes
         self.mvcc_manager.rollback_dot_states(self.id)?;
 
-        // Remove from deadlock detector
+        // Remove from deadlock detector, forgetting it if it was a victim
         self.deadlock_detector.remove_transaction(self.id);
+        self.deadlock_detector.take_victim(self.id);
 
         // Update the state
         self.state = TransactionState::Aborted;
@@ -520,10 +555,20 @@ pub struct TransactionManager {
 impl TransactionManager {
     /// Create a new transaction manager
     pub fn new(buffer_manager: Arc<BufferManager>, wal: Arc<WriteAheadLog>) -> Self {
+        Self::with_deadlock_detector(buffer_manager, wal, DeadlockDetector::default())
+    }
+
+    /// Create a transaction manager choosing deadlock victims by the policy in `config`
+    pub fn with_storage_config(buffer_manager: Arc<BufferManager>, wal: Arc<WriteAheadLog>, config: &StorageConfig) -> Self {
+        Self::with_deadlock_detector(buffer_manager, wal, DeadlockDetector::for_storage(config))
+    }
+
+    /// Create a transaction manager whose lock waits are tracked by `deadlock_detector`
+    pub fn with_deadlock_detector(buffer_manager: Arc<BufferManager>, wal: Arc<WriteAheadLog>, deadlock_detector: DeadlockDetector) -> Self {
+        let deadlock_detector = Arc::new(deadlock_detector);
         let mvcc_manager = Arc::new(MVCCManager::new());
-        let lock_manager = Arc::new(LockManager::new());
+        let lock_manager = Arc::new(LockManager::new().with_deadlock_detector(deadlock_detector.clone()));
         let isolation_enforcer = Arc::new(IsolationLevelEnforcer::new(mvcc_manager.clone(), lock_manager.clone()));
-        let deadlock_detector = Arc::new(DeadlockDetector::default());
 
         // Initialize OCC components
         let occ_manager = Arc::new(OCCManager::new(
@@ -868,6 +913,16 @@ impl TransactionManager {
     pub fn track_page_write(&self, txn_id: u64, page_id: PageId) -> StorageResult<()> {
         self.occ_transaction_manager.add_to_write_set(txn_id, page_id)
     }
+
+    /// Get the deadlock detector tracking this manager's lock waits
+    pub fn deadlock_detector(&self) -> Arc<DeadlockDetector> {
+        self.deadlock_detector.clone()
+    }
+
+    /// Snapshot of which transactions are waiting on which
+    pub fn wait_for_graph(&self) -> WaitForGraphSnapshot {
+        self.deadlock_detector.wait_for_graph()
+    }
 }
 
 impl crate::storage_engine::lib::Initializable for TransactionManager {
@@ -943,11 +998,18 @@ impl ConcurrentTransactionManager {
         let inner = self.inner.read().unwrap();
         Ok(inner.active_transaction_ids())
     }
+
+    /// Snapshot of which transactions are waiting on which
+    pub fn wait_for_graph(&self) -> StorageResult<WaitForGraphSnapshot> {
+        let inner = self.inner.read().unwrap();
+        Ok(inner.wait_for_graph())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage_engine::eviction::ReplacementPolicy;
     use crate::storage_engine::file_format::FileFormat;
     use crate::storage_engine::lib::{Initializable, StorageConfig};
     use std::sync::Mutex;
@@ -961,11 +1023,16 @@ mod tests {
             path: path.clone(),
             page_size: 4096,
             buffer_pool_size: 100,
+            eviction_policy: ReplacementPolicy::LRU,
             direct_io: false,
+            io_backend: Default::default(),
             wal_size: 1024 * 1024,
             flush_interval_ms: 1000,
             max_dirty_pages: 10,
             writer_threads: 1,
+            compaction_threads: None,
+            compaction_io_budget_bytes_per_sec: 0,
+            deadlock_policy: Default::default(),
         };
 
         let mut file_format = FileFormat::new(config.clone());
@@ -981,6 +1048,8 @@ mod tests {
             directory: path.parent().unwrap().to_path_buf(),
             max_file_size: 64 * 1024 * 1024,
             direct_io: false,
+            archive: None,
+            group_commit: Some(crate::storage_engine::group_commit::GroupCommitConfig::default()),
         };
         let wal = WriteAheadLog::new(wal_config).unwrap();
         let wal = Arc::new(wal);
@@ -0,0 +1,510 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Background vacuum of old MVCC versions
+//!
+//! The vacuum walks version chains a bounded number of pages at a time, resuming
+//! where the previous cycle stopped, and removes versions that are behind the
+//! oldest active snapshot and outside the retention window. Versions seen by a
+//! named snapshot are always kept.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::storage_engine::file_format::PageId;
+use crate::storage_engine::mvcc::{MVCCManager, RetentionPolicy, Timestamp, VersionInfo};
+
+/// File the vacuum publishes its status to, relative to the data directory
+pub const VACUUM_STATUS_FILE: &str = "vacuum_status.json";
+
+/// File `dotdb vacuum run` queues collections in for the running vacuum, relative to the data directory
+pub const VACUUM_REQUESTS_FILE: &str = "vacuum_requests.json";
+
+/// Longest the vacuum thread sleeps between status updates
+const VACUUM_TICK: Duration = Duration::from_secs(1);
+
+/// Background vacuum configuration
+#[derive(Debug, Clone)]
+pub struct VacuumConfig {
+    /// History kept beyond what active transactions need
+    pub retention: RetentionPolicy,
+    /// How often a background cycle runs
+    pub interval: Duration,
+    /// Most pages visited per cycle
+    pub max_pages_per_cycle: usize,
+    /// Most versions removed per cycle
+    pub max_versions_per_cycle: usize,
+    /// Where to publish status for `dotdb vacuum status`, if anywhere
+    pub status_path: Option<PathBuf>,
+    /// Where to pick up collections queued by `dotdb vacuum run`, if anywhere
+    pub requests_path: Option<PathBuf>,
+}
+
+impl Default for VacuumConfig {
+    fn default() -> Self {
+        Self {
+            retention: RetentionPolicy::default(),
+            interval: Duration::from_secs(10),
+            max_pages_per_cycle: 256,
+            max_versions_per_cycle: 4096,
+            status_path: None,
+            requests_path: None,
+        }
+    }
+}
+
+impl VacuumConfig {
+    /// Configuration publishing status and reading requests in the given data directory
+    pub fn for_data_dir(data_dir: &Path) -> Self {
+        Self {
+            status_path: Some(data_dir.join(VACUUM_STATUS_FILE)),
+            requests_path: Some(data_dir.join(VACUUM_REQUESTS_FILE)),
+            ..Self::default()
+        }
+    }
+}
+
+/// Outcome of a single vacuum run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VacuumRunReport {
+    /// Collection the run was limited to, if any
+    pub collection: Option<String>,
+    /// Oldest active snapshot timestamp the run stayed behind
+    pub watermark: Timestamp,
+    pub pages_scanned: usize,
+    pub versions_reclaimed: u64,
+    pub bytes_reclaimed: u64,
+    /// Whether the run reached the last page rather than stopping at the per-cycle budget
+    pub completed: bool,
+    pub duration_ms: u64,
+    /// Unix time in seconds the run finished
+    pub finished_at: u64,
+}
+
+/// Snapshot of the vacuum's progress, as published to the status file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VacuumStatus {
+    pub running: bool,
+    pub watermark: Timestamp,
+    pub keep_history: usize,
+    pub retain_for_secs: u64,
+    pub named_snapshots: Vec<String>,
+    pub total_versions: usize,
+    pub total_pages: usize,
+    pub runs: u64,
+    pub versions_reclaimed: u64,
+    pub bytes_reclaimed: u64,
+    pub last_run: Option<VacuumRunReport>,
+    /// Collections queued for a full vacuum
+    pub pending_collections: Vec<String>,
+    /// Unix time in seconds the snapshot was taken
+    pub updated_at: u64,
+}
+
+impl VacuumStatus {
+    /// Reads status previously published by a running vacuum.
+    pub fn read_from(path: &Path) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        serde_json::from_slice(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Queues a full vacuum of `collection` for the vacuum running against the data directory.
+pub fn request_collection_vacuum(requests_path: &Path, collection: &str) -> io::Result<()> {
+    let mut pending = read_requests(requests_path)?;
+    if !pending.iter().any(|queued| queued == collection) {
+        pending.push(collection.to_string());
+    }
+    write_atomically(requests_path, &serde_json::to_vec_pretty(&pending).map_err(io::Error::other)?)
+}
+
+fn read_requests(path: &Path) -> io::Result<Vec<String>> {
+    match fs::read(path) {
+        Ok(data) => serde_json::from_slice(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+fn write_atomically(path: &Path, data: &[u8]) -> io::Result<()> {
+    // Write then rename so readers never see a partial file
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, data)?;
+    fs::rename(&tmp, path)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+#[derive(Default)]
+struct Totals {
+    runs: u64,
+    versions_reclaimed: u64,
+    bytes_reclaimed: u64,
+    last_run: Option<VacuumRunReport>,
+}
+
+/// Removes old versions from an MVCC manager in bounded steps
+pub struct Vacuum {
+    mvcc: Arc<MVCCManager>,
+    config: VacuumConfig,
+    /// Raw id of the last page the background cycle visited
+    cursor: Mutex<Option<u64>>,
+    pending: Mutex<VecDeque<String>>,
+    totals: Mutex<Totals>,
+    running: AtomicBool,
+}
+
+impl Vacuum {
+    /// Creates a vacuum over `mvcc`, applying the configured retention policy to it
+    pub fn new(mvcc: Arc<MVCCManager>, config: VacuumConfig) -> Self {
+        mvcc.set_retention_policy(config.retention);
+        Self {
+            mvcc,
+            config,
+            cursor: Mutex::new(None),
+            pending: Mutex::new(VecDeque::new()),
+            totals: Mutex::new(Totals::default()),
+            running: AtomicBool::new(false),
+        }
+    }
+
+    /// Runs one bounded cycle over all pages, continuing after the page the previous cycle stopped at
+    pub fn run_cycle(&self) -> VacuumRunReport {
+        let mut cursor = self.cursor.lock().unwrap();
+        let after = *cursor;
+        let pages: Vec<PageId> = self.mvcc.versioned_pages().into_iter().filter(|page_id| after.is_none_or(|after| page_id.0 > after)).collect();
+
+        let report = self.vacuum_pages(None, &pages, self.config.max_pages_per_cycle);
+        *cursor = if report.completed {
+            None
+        } else {
+            report.pages_scanned.checked_sub(1).map_or(after, |last| Some(pages[last].0))
+        };
+        report
+    }
+
+    /// Vacuums every page of a collection, a cycle's worth of versions at a time
+    pub fn vacuum_collection(&self, collection: &str) -> VacuumRunReport {
+        let pages = self.mvcc.collection_pages(collection);
+        self.vacuum_pages(Some(collection.to_string()), &pages, usize::MAX)
+    }
+
+    /// Queues a full vacuum of a collection for the next background cycle
+    pub fn request_collection(&self, collection: &str) {
+        let mut pending = self.pending.lock().unwrap();
+        if !pending.iter().any(|queued| queued == collection) {
+            pending.push_back(collection.to_string());
+        }
+    }
+
+    fn vacuum_pages(&self, collection: Option<String>, pages: &[PageId], max_pages: usize) -> VacuumRunReport {
+        let started = Instant::now();
+        let horizon = self.mvcc.gc_horizon();
+        let mut report = VacuumRunReport {
+            collection,
+            watermark: horizon.watermark,
+            completed: true,
+            ..VacuumRunReport::default()
+        };
+
+        // Collection runs keep going past the per-cycle budget, but only ever lock one page at a time
+        let per_cycle = self.config.max_versions_per_cycle.max(1);
+        let mut budget = per_cycle;
+        for &page_id in pages {
+            if report.pages_scanned >= max_pages || budget == 0 {
+                report.completed = false;
+                break;
+            }
+            report.pages_scanned += 1;
+
+            loop {
+                let removed = self.mvcc.vacuum_page(page_id, &horizon, budget);
+                budget -= removed.len();
+                report.versions_reclaimed += removed.len() as u64;
+                report.bytes_reclaimed += removed.iter().map(Self::version_size).sum::<u64>();
+                if budget > 0 || report.collection.is_none() {
+                    break;
+                }
+                budget = per_cycle;
+                thread::yield_now();
+            }
+        }
+
+        report.duration_ms = started.elapsed().as_millis() as u64;
+        report.finished_at = unix_now();
+        if report.versions_reclaimed > 0 {
+            info!(
+                "Vacuum reclaimed {} versions ({} bytes) from {} pages",
+                report.versions_reclaimed, report.bytes_reclaimed, report.pages_scanned
+            );
+        }
+
+        let mut totals = self.totals.lock().unwrap();
+        totals.runs += 1;
+        totals.versions_reclaimed += report.versions_reclaimed;
+        totals.bytes_reclaimed += report.bytes_reclaimed;
+        totals.last_run = Some(report.clone());
+        report
+    }
+
+    fn version_size(version: &VersionInfo) -> u64 {
+        version.data.data.len() as u64
+    }
+
+    /// Moves collections queued through the requests file into the pending queue
+    fn take_file_requests(&self) {
+        let Some(path) = &self.config.requests_path else {
+            return;
+        };
+        let requests = match read_requests(path) {
+            Ok(requests) if requests.is_empty() => return,
+            Ok(requests) => requests,
+            Err(e) => {
+                warn!("Failed to read vacuum requests from {}: {}", path.display(), e);
+                return;
+            }
+        };
+        if let Err(e) = fs::remove_file(path) {
+            warn!("Failed to clear vacuum requests at {}: {}", path.display(), e);
+            return;
+        }
+        for collection in requests {
+            self.request_collection(&collection);
+        }
+    }
+
+    /// Runs the next queued collection vacuum, if any
+    fn run_pending(&self) -> Option<VacuumRunReport> {
+        let collection = self.pending.lock().unwrap().pop_front()?;
+        Some(self.vacuum_collection(&collection))
+    }
+
+    /// Returns a snapshot of the vacuum's progress.
+    pub fn status(&self) -> VacuumStatus {
+        let statistics = self.mvcc.get_statistics();
+        let retention = self.mvcc.retention_policy();
+        let totals = self.totals.lock().unwrap();
+
+        VacuumStatus {
+            running: self.running.load(Ordering::Relaxed),
+            watermark: self.mvcc.oldest_active_timestamp(),
+            keep_history: retention.keep_history,
+            retain_for_secs: retention.retain_for.as_secs(),
+            named_snapshots: self.mvcc.named_snapshots().into_iter().map(|(name, _)| name).collect(),
+            total_versions: statistics.total_versions,
+            total_pages: statistics.total_pages,
+            runs: totals.runs,
+            versions_reclaimed: totals.versions_reclaimed,
+            bytes_reclaimed: totals.bytes_reclaimed,
+            last_run: totals.last_run.clone(),
+            pending_collections: self.pending.lock().unwrap().iter().cloned().collect(),
+            updated_at: unix_now(),
+        }
+    }
+
+    fn publish_status(&self) {
+        let Some(path) = &self.config.status_path else {
+            return;
+        };
+        let result = serde_json::to_vec_pretty(&self.status()).map_err(io::Error::other).and_then(|data| write_atomically(path, &data));
+        if let Err(e) = result {
+            warn!("Failed to publish vacuum status to {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Runs a [`Vacuum`] on a background thread
+pub struct VacuumService {
+    vacuum: Arc<Vacuum>,
+    shutdown: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl VacuumService {
+    /// Creates a service for the vacuum. Call [`start`](Self::start) to begin vacuuming.
+    pub fn new(vacuum: Arc<Vacuum>) -> Self {
+        Self {
+            vacuum,
+            shutdown: Arc::new(AtomicBool::new(false)),
+            handle: None,
+        }
+    }
+
+    /// The vacuum this service drives
+    pub fn vacuum(&self) -> Arc<Vacuum> {
+        Arc::clone(&self.vacuum)
+    }
+
+    /// Starts the vacuum thread.
+    pub fn start(&mut self) -> io::Result<()> {
+        if self.vacuum.running.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+        self.shutdown.store(false, Ordering::Release);
+
+        let vacuum = Arc::clone(&self.vacuum);
+        let shutdown = Arc::clone(&self.shutdown);
+        let handle = thread::Builder::new().name("mvcc-vacuum".to_string()).spawn(move || {
+            let mut last_cycle: Option<Instant> = None;
+            while !shutdown.load(Ordering::Relaxed) {
+                vacuum.take_file_requests();
+                if vacuum.run_pending().is_none() && last_cycle.is_none_or(|at| at.elapsed() >= vacuum.config.interval) {
+                    vacuum.run_cycle();
+                    last_cycle = Some(Instant::now());
+                }
+                vacuum.publish_status();
+                thread::sleep(VACUUM_TICK.min(vacuum.config.interval));
+            }
+        });
+        match handle {
+            Ok(handle) => self.handle = Some(handle),
+            Err(e) => {
+                self.vacuum.running.store(false, Ordering::Release);
+                return Err(e);
+            }
+        }
+
+        info!("MVCC vacuum started, running every {:?}", self.vacuum.config.interval);
+        Ok(())
+    }
+
+    /// Signals the vacuum thread to stop and waits for it.
+    pub fn stop(&mut self) {
+        let Some(handle) = self.handle.take() else {
+            return;
+        };
+        self.shutdown.store(true, Ordering::Release);
+        let _ = handle.join();
+        self.vacuum.running.store(false, Ordering::Release);
+        self.vacuum.publish_status();
+    }
+}
+
+impl Drop for VacuumService {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage_engine::file_format::{Page, PageType};
+    use crate::storage_engine::lib::VersionId;
+    use crate::storage_engine::transaction::IsolationLevel;
+    use tempfile::tempdir;
+
+    fn write_versions(mvcc: &MVCCManager, page_id: PageId, count: u64, first_txn: u64) {
+        for txn_id in first_txn..first_txn + count {
+            mvcc.create_snapshot(txn_id, IsolationLevel::ReadCommitted).unwrap();
+            mvcc.add_version(page_id, Arc::new(Page::new(page_id, PageType::Data, VersionId(txn_id), 4096)), txn_id).unwrap();
+            mvcc.commit_transaction(txn_id).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_cycles_are_bounded_and_resume() {
+        let mvcc = Arc::new(MVCCManager::new());
+        for page in 1..=5 {
+            write_versions(&mvcc, PageId(page), 3, page * 10);
+        }
+        let config = VacuumConfig {
+            max_pages_per_cycle: 2,
+            ..VacuumConfig::default()
+        };
+        let vacuum = Vacuum::new(Arc::clone(&mvcc), config);
+
+        let first = vacuum.run_cycle();
+        assert_eq!(first.pages_scanned, 2);
+        assert_eq!(first.versions_reclaimed, 4);
+        assert!(first.bytes_reclaimed > 0);
+        assert!(!first.completed);
+
+        let second = vacuum.run_cycle();
+        assert_eq!(second.pages_scanned, 2);
+        let third = vacuum.run_cycle();
+        assert_eq!(third.pages_scanned, 1);
+        assert!(third.completed);
+        assert_eq!(mvcc.get_statistics().total_versions, 5);
+
+        let status = vacuum.status();
+        assert_eq!(status.runs, 3);
+        assert_eq!(status.versions_reclaimed, 10);
+    }
+
+    #[test]
+    fn test_collection_vacuum_only_touches_its_pages() {
+        let mvcc = Arc::new(MVCCManager::new());
+        write_versions(&mvcc, PageId(1), 4, 10);
+        write_versions(&mvcc, PageId(2), 4, 20);
+        mvcc.assign_page(PageId(1), "users");
+        mvcc.assign_page(PageId(2), "orders");
+
+        let config = VacuumConfig {
+            max_versions_per_cycle: 1,
+            ..VacuumConfig::default()
+        };
+        let vacuum = Vacuum::new(Arc::clone(&mvcc), config);
+        let report = vacuum.vacuum_collection("users");
+
+        assert_eq!(report.collection.as_deref(), Some("users"));
+        assert_eq!(report.versions_reclaimed, 3);
+        assert!(report.completed);
+        assert_eq!(mvcc.get_statistics().total_versions, 5);
+    }
+
+    #[test]
+    fn test_service_picks_up_requests_and_publishes_status() {
+        let dir = tempdir().unwrap();
+        let mvcc = Arc::new(MVCCManager::new());
+        write_versions(&mvcc, PageId(1), 3, 10);
+        mvcc.assign_page(PageId(1), "users");
+
+        request_collection_vacuum(&dir.path().join(VACUUM_REQUESTS_FILE), "users").unwrap();
+        request_collection_vacuum(&dir.path().join(VACUUM_REQUESTS_FILE), "users").unwrap();
+
+        let config = VacuumConfig {
+            interval: Duration::from_secs(3600),
+            ..VacuumConfig::for_data_dir(dir.path())
+        };
+        let mut service = VacuumService::new(Arc::new(Vacuum::new(mvcc, config)));
+        service.start().unwrap();
+
+        let status_path = dir.path().join(VACUUM_STATUS_FILE);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !status_path.exists() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        service.stop();
+
+        let status = VacuumStatus::read_from(&status_path).unwrap();
+        assert!(!status.running);
+        assert!(status.pending_collections.is_empty());
+        assert_eq!(status.versions_reclaimed, 2);
+        assert!(!dir.path().join(VACUUM_REQUESTS_FILE).exists());
+    }
+}
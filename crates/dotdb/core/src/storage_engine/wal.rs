@@ -18,13 +18,18 @@
 // This module provides durability and crash recovery by logging all changes before they are applied to the main storage. It implements a write-ahead log (WAL) with support for log records, file rotation, checkpoints, and replay for recovery.
 
 use std::convert::TryInto;
+use std::fmt;
 use std::fs::{File, OpenOptions};
 use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 
-use crate::storage_engine::file_format::{Page, PageId};
-use crate::storage_engine::lib::{Flushable, Initializable, StorageError, StorageResult, VersionId};
+use crate::storage_engine::failpoint::{self, Failpoint};
+use crate::storage_engine::file_format::{Page, PageHeader, PageId, PageType};
+use crate::storage_engine::group_commit::{GroupCommitConfig, GroupCommitStats, GroupCommitter};
+use crate::storage_engine::lib::{Flushable, Initializable, StorageError, StorageResult, VersionId, generate_timestamp};
+use crate::storage_engine::wal_archive::{self, RecoveryReport, RecoveryTarget, WalArchive, WalArchiveConfig};
 
 /// Magic number to identify WAL files (DOTWAL)
 const WAL_MAGIC: [u8; 4] = [0x44, 0x4F, 0x54, 0x57];
@@ -33,7 +38,7 @@ const WAL_VERSION: u32 = 1;
 /// Size of the WAL header in bytes
 const WAL_HEADER_SIZE: usize = 128;
 /// Size of a record header in bytes - must be large enough for all fields
-const RECORD_HEADER_SIZE: usize = 37; // 37 byte: serialize fonksiyonundaki header_size ile uyumlu
+pub(crate) const RECORD_HEADER_SIZE: usize = 37; // 37 byte: serialize fonksiyonundaki header_size ile uyumlu
 
 /// Types of WAL records
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -81,13 +86,19 @@ pub struct LogSequenceNumber {
     pub offset: u64,
 }
 
+impl fmt::Display for LogSequenceNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.file_id, self.offset)
+    }
+}
+
 /// Header for a WAL record
 #[derive(Debug, Clone)]
-struct RecordHeader {
+pub(crate) struct RecordHeader {
     /// Type of record
     record_type: RecordType,
     /// Log sequence number
-    lsn: LogSequenceNumber,
+    pub(crate) lsn: LogSequenceNumber,
     /// Transaction ID (if applicable)
     transaction_id: u64,
     /// Page ID (for Write records)
@@ -95,7 +106,7 @@ struct RecordHeader {
     /// Checksum of the record content
     checksum: u32,
     /// Length of the record data
-    data_length: u32,
+    pub(crate) data_length: u32,
 }
 
 impl RecordHeader {
@@ -141,11 +152,14 @@ impl RecordHeader {
     }
 
     /// Deserialize the header from bytes
-    fn deserialize(buffer: &[u8]) -> StorageResult<Self> {
+    pub(crate) fn deserialize(buffer: &[u8]) -> StorageResult<Self> {
         if buffer.len() < RECORD_HEADER_SIZE {
             return Err(StorageError::Io(io::Error::new(io::ErrorKind::InvalidInput, "Buffer too small for record header")));
         }
 
+        if buffer[0] > RecordType::Read as u8 {
+            return Err(StorageError::Corruption(format!("Invalid record type: {}", buffer[0])));
+        }
         let record_type = RecordType::from(buffer[0]);
 
         let lsn = LogSequenceNumber {
@@ -178,9 +192,9 @@ impl RecordHeader {
 #[derive(Debug, Clone)]
 pub struct LogEntry {
     /// Header of the record
-    header: RecordHeader,
+    pub(crate) header: RecordHeader,
     /// Data of the record
-    data: Vec<u8>,
+    pub(crate) data: Vec<u8>,
 }
 
 impl LogEntry {
@@ -190,10 +204,15 @@ impl LogEntry {
         Self { header, data: Vec::new() }
     }
 
-    /// Create a new commit transaction record
+    /// Create a new commit transaction record, stamped with the commit time for point-in-time recovery
     pub fn commit_transaction(lsn: LogSequenceNumber, transaction_id: u64) -> Self {
-        let header = RecordHeader::new(RecordType::Commit, lsn, transaction_id, PageId(0));
-        Self { header, data: Vec::new() }
+        let mut header = RecordHeader::new(RecordType::Commit, lsn, transaction_id, PageId(0));
+        let data = generate_timestamp().to_le_bytes().to_vec();
+        header.data_length = data.len() as u32;
+
+        let mut entry = Self { header, data };
+        entry.header.checksum = entry.calculate_checksum();
+        entry
     }
 
     /// Create a new abort transaction record
@@ -263,6 +282,30 @@ impl LogEntry {
         self.header.page_id
     }
 
+    /// Commit time in nanoseconds since the Unix epoch, for commit records that carry one
+    pub fn commit_timestamp(&self) -> Option<u64> {
+        if self.header.record_type != RecordType::Commit {
+            return None;
+        }
+        self.data.get(0..8).and_then(|bytes| bytes.try_into().ok()).map(u64::from_le_bytes)
+    }
+
+    /// Rebuild the page image carried by a page write record
+    pub fn page(&self) -> Option<Page> {
+        if self.header.record_type != RecordType::Write || self.data.len() < 9 {
+            return None;
+        }
+
+        let version = VersionId(u64::from_le_bytes(self.data[1..9].try_into().ok()?));
+        let mut header = PageHeader::new(PageType::from(self.data[0]), version);
+        let data = self.data[9..].to_vec();
+        header.data_size = data.len().min(u16::MAX as usize) as u16;
+
+        let mut page = Page { id: self.header.page_id, header, data };
+        page.update_checksum();
+        Some(page)
+    }
+
     /// Verify the checksum
     pub fn verify_checksum(&self) -> bool {
         self.header.checksum == self.calculate_checksum()
@@ -391,6 +434,10 @@ pub struct WalConfig {
     pub max_file_size: u64,
     /// Whether to use direct I/O
     pub direct_io: bool,
+    /// Where completed segments are archived before they are removed; `None` disables archiving
+    pub archive: Option<WalArchiveConfig>,
+    /// Batching of durable appends into shared fsyncs; `None` syncs every durable append on its own
+    pub group_commit: Option<GroupCommitConfig>,
 }
 
 impl Default for WalConfig {
@@ -399,6 +446,8 @@ impl Default for WalConfig {
             directory: PathBuf::from("./wal"),
             max_file_size: 64 * 1024 * 1024, // 64 MB
             direct_io: false,
+            archive: None,
+            group_commit: Some(GroupCommitConfig::default()),
         }
     }
 }
@@ -417,6 +466,12 @@ pub struct WriteAheadLog {
     current_lsn: Mutex<LogSequenceNumber>,
     /// Maximum transaction ID encountered
     max_txn_id: Mutex<u64>,
+    /// Archive for completed segments
+    archive: Option<WalArchive>,
+    /// Batches durable appends from concurrent committers
+    group_commit: Option<GroupCommitter>,
+    /// Number of fsyncs issued on WAL files
+    fsyncs: AtomicU64,
 }
 
 impl WriteAheadLog {
@@ -425,23 +480,58 @@ impl WriteAheadLog {
         // Create the directory if it doesn't exist
         std::fs::create_dir_all(&config.directory)?;
 
-        // Create or open the first WAL file
-        let file_path = config.directory.join("wal.0000");
-        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(file_path)?;
+        // Create the first WAL file or reopen the newest one, so LSNs keep increasing across restarts
+        let file_id = Self::newest_segment_id(&config.directory)?;
+        let file_path = config.directory.join(format!("wal.{file_id:04}"));
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(file_path)?;
 
-        // Get the file size
-        let size = file.metadata()?.len();
+        // A crash can leave a partly written record at the end of the segment; new records
+        // must follow the last complete one or recovery would stop at the torn record
+        let size = Self::truncate_torn_tail(file_id, &mut file)?;
+
+        let archive = config.archive.clone().map(WalArchive::open).transpose()?;
+        let group_commit = config.group_commit.clone().map(GroupCommitter::new);
 
         Ok(Self {
             config,
             current_file: Mutex::new(file),
             current_size: Mutex::new(size),
-            current_file_id: Mutex::new(0),
+            current_file_id: Mutex::new(file_id),
             current_lsn: Mutex::new(LogSequenceNumber::default()),
             max_txn_id: Mutex::new(0),
+            archive,
+            group_commit,
+            fsyncs: AtomicU64::new(0),
         })
     }
 
+    /// ID of the newest segment in `directory`, 0 if there is none yet
+    fn newest_segment_id(directory: &Path) -> StorageResult<u32> {
+        let mut newest = 0;
+        for entry in std::fs::read_dir(directory)? {
+            if let Some(id) = entry?.file_name().to_str().and_then(|name| name.strip_prefix("wal.")).and_then(|id| id.parse::<u32>().ok()) {
+                newest = newest.max(id);
+            }
+        }
+        Ok(newest)
+    }
+
+    /// Drop an incomplete record at the end of a segment, returning the segment's size.
+    /// Damage anywhere else is left for recovery to report.
+    fn truncate_torn_tail(file_id: u32, file: &mut File) -> StorageResult<u64> {
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        let Ok(entries) = wal_archive::parse_segment(file_id, &data, true) else {
+            return Ok(data.len() as u64);
+        };
+
+        let valid_end = entries.last().map_or(0, |entry| entry.lsn().offset + entry.serialized_size() as u64);
+        if valid_end < data.len() as u64 {
+            file.set_len(valid_end)?;
+        }
+        Ok(valid_end)
+    }
+
     /// Get the current LSN
     pub fn current_lsn(&self) -> LogSequenceNumber {
         *self.current_lsn.lock().unwrap()
@@ -462,8 +552,8 @@ impl WriteAheadLog {
     ///
     /// Steps:
     /// 1. Update the max transaction ID if needed.
-    /// 2. Clone and serialize the entry, updating its LSN and checksum.
-    /// 3. Check if file rotation is needed; rotate if necessary.
+    /// 2. Check if file rotation is needed; rotate if necessary.
+    /// 3. Clone and serialize the entry, updating its LSN and checksum.
     /// 4. Write the entry to the WAL file and update the file size.
     /// 5. Return the LSN of the appended entry.
     pub fn append(&self, entry: &LogEntry) -> StorageResult<LogSequenceNumber> {
@@ -475,6 +565,16 @@ impl WriteAheadLog {
             }
         }
 
+        // Check if we need to rotate the file; the LSN depends on which file the entry lands in
+        let record_size = entry.serialized_size() as u64;
+        let needs_rotation = {
+            let size = self.current_size.lock().unwrap();
+            *size > 0 && *size + record_size > self.config.max_file_size
+        };
+        if needs_rotation {
+            self.rotate_file()?;
+        }
+
         // Serialize the entry
         let mut entry = entry.clone();
         let file_id = *self.current_file_id.lock().unwrap();
@@ -490,18 +590,9 @@ impl WriteAheadLog {
         let mut file = self.current_file.lock().unwrap();
         let mut size = self.current_size.lock().unwrap();
 
-        // Check if we need to rotate the file
-        if *size + full_data.len() as u64 > self.config.max_file_size {
-            self.rotate_file()?;
-
-            // Get the new file and size
-            file = self.current_file.lock().unwrap();
-            size = self.current_size.lock().unwrap();
-        }
-
         // Write the entry
         file.seek(SeekFrom::End(0))?;
-        file.write_all(&full_data)?;
+        failpoint::write_all(Failpoint::WalAppend, &mut file, || self.segment_path(file_id), &full_data)?;
 
         // Update the current size
         *size += full_data.len() as u64;
@@ -513,23 +604,47 @@ impl WriteAheadLog {
     ///
     /// Steps:
     /// 1. Acquire all relevant mutexes (file_id, file, size) in order.
-    /// 2. Increment the file ID and create a new WAL file.
+    /// 2. Sync the completed file, increment the file ID and create a new WAL file.
     /// 3. Replace the current file and reset the size.
-    /// 4. Return Ok or error.
+    /// 4. Archive the completed file if archiving is enabled.
     fn rotate_file(&self) -> StorageResult<()> {
-        // Acquire all mutexes at the same time and in order
-        let mut file_id = self.current_file_id.lock().unwrap();
-        let mut file = self.current_file.lock().unwrap();
-        let mut size = self.current_size.lock().unwrap();
+        let completed_id = {
+            // Acquire all mutexes at the same time and in order
+            let mut file_id = self.current_file_id.lock().unwrap();
+            let mut file = self.current_file.lock().unwrap();
+            let mut size = self.current_size.lock().unwrap();
+
+            failpoint::sync_all(Failpoint::WalSync, &file, || self.config.directory.clone())?;
+            self.fsyncs.fetch_add(1, Ordering::Relaxed);
+
+            let completed_id = *file_id;
+            *file_id += 1;
+            let file_path = self.segment_path(*file_id);
+            let new_file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(file_path)?;
+            *file = new_file;
+            *size = 0;
+            completed_id
+        };
 
-        *file_id += 1;
-        let file_path = self.config.directory.join(format!("wal.{:04}", *file_id));
-        let new_file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(file_path)?;
-        *file = new_file;
-        *size = 0;
+        if let Some(archive) = &self.archive {
+            archive.archive_segment(completed_id, &self.segment_path(completed_id))?;
+        }
         Ok(())
     }
 
+    /// Path of the WAL segment with the given ID
+    fn segment_path(&self, file_id: u32) -> PathBuf {
+        self.config.directory.join(format!("wal.{file_id:04}"))
+    }
+
+    /// Make sure a segment is in the archive before its file is removed
+    fn archive_before_removal(&self, file_id: u32, path: &Path) -> StorageResult<()> {
+        match &self.archive {
+            Some(archive) if !archive.is_archived(file_id) => archive.archive_segment(file_id, path).map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+
     /// Flush the WAL to disk
     pub fn flush(&self) -> StorageResult<()> {
         let mut file = self.current_file.lock().unwrap();
@@ -537,12 +652,45 @@ impl WriteAheadLog {
 
         #[cfg(unix)]
         {
-            file.sync_all()?;
+            failpoint::sync_all(Failpoint::WalSync, &file, || self.config.directory.clone())?;
+            self.fsyncs.fetch_add(1, Ordering::Relaxed);
         }
 
         Ok(())
     }
 
+    /// Appends a log entry and returns once it is durable.
+    ///
+    /// With group commit enabled, concurrent callers share a single fsync; otherwise the
+    /// entry is appended and the WAL flushed. Either way an `Ok` means the entry is on disk.
+    pub fn append_durable(&self, entry: &LogEntry) -> StorageResult<LogSequenceNumber> {
+        match &self.group_commit {
+            Some(group_commit) => group_commit.commit(entry, |batch| self.write_batch(batch)),
+            None => {
+                let lsn = self.append(entry)?;
+                self.flush()?;
+                Ok(lsn)
+            }
+        }
+    }
+
+    /// Appends a batch of entries and syncs them with a single flush
+    fn write_batch(&self, entries: &[LogEntry]) -> StorageResult<Vec<LogSequenceNumber>> {
+        let lsns = entries.iter().map(|entry| self.append(entry)).collect::<StorageResult<Vec<_>>>()?;
+        self.flush()?;
+        Ok(lsns)
+    }
+
+    /// Number of fsyncs issued on WAL files since the WAL was opened
+    pub fn fsync_count(&self) -> u64 {
+        self.fsyncs.load(Ordering::Relaxed)
+    }
+
+    /// Group commit statistics, or `None` when group commit is disabled
+    pub fn group_commit_stats(&self) -> Option<GroupCommitStats> {
+        self.group_commit.as_ref().map(GroupCommitter::get_statistics)
+    }
+
     /// Get the maximum transaction ID encountered
     pub fn max_transaction_id(&self) -> StorageResult<u64> {
         Ok(*self.max_txn_id.lock().unwrap())
@@ -585,6 +733,7 @@ impl WriteAheadLog {
                 && let Ok(id) = id_str.parse::<u32>()
                 && id < current_file_id
             {
+                self.archive_before_removal(id, &file_path)?;
                 std::fs::remove_file(file_path)?;
             }
         }
@@ -614,12 +763,77 @@ impl WriteAheadLog {
                 && let Ok(id) = id_str.parse::<u32>()
                 && id < before_file_id
             {
+                self.archive_before_removal(id, &path)?;
                 let _ = std::fs::remove_file(path);
             }
         }
         Ok(())
     }
 
+    /// The segment archive, if archiving is enabled
+    pub fn archive(&self) -> Option<&WalArchive> {
+        self.archive.as_ref()
+    }
+
+    /// Replays archived and live segments from the last checkpoint up to `timestamp`.
+    ///
+    /// Only transactions whose commit record is stamped at or before `timestamp` are passed
+    /// to `apply`, in log order. The caller is responsible for restoring the data files to
+    /// the checkpoint reported in the returned `RecoveryReport` before applying.
+    pub fn recover_to_timestamp<F>(&self, timestamp: u64, apply: F) -> StorageResult<RecoveryReport>
+    where
+        F: FnMut(&LogEntry) -> StorageResult<()>,
+    {
+        self.flush()?;
+        wal_archive::recover(self.archive.as_ref(), &self.config.directory, RecoveryTarget::Timestamp(timestamp), |_| Ok(()), apply)
+    }
+
+    /// Replays archived and live segments from the last checkpoint up to and including `lsn`.
+    ///
+    /// Transactions that have not committed at `lsn` are discarded.
+    pub fn recover_to_lsn<F>(&self, lsn: LogSequenceNumber, apply: F) -> StorageResult<RecoveryReport>
+    where
+        F: FnMut(&LogEntry) -> StorageResult<()>,
+    {
+        self.flush()?;
+        wal_archive::recover(self.archive.as_ref(), &self.config.directory, RecoveryTarget::Lsn(lsn), |_| Ok(()), apply)
+    }
+
+    /// Rebuilds the data files as they were at `target`.
+    ///
+    /// `restore_base` is called once the checkpoint replay starts from is known, with `None`
+    /// when replay starts at the beginning of the log, and must restore the data files to it
+    /// before the records up to `target` are handed to `apply`.
+    pub fn restore_to<R, F>(&self, target: RecoveryTarget, restore_base: R, apply: F) -> StorageResult<RecoveryReport>
+    where
+        R: FnOnce(Option<LogSequenceNumber>) -> StorageResult<()>,
+        F: FnMut(&LogEntry) -> StorageResult<()>,
+    {
+        self.flush()?;
+        wal_archive::recover(self.archive.as_ref(), &self.config.directory, target, restore_base, apply)
+    }
+
+    /// Writes a checkpoint record, first storing the page store at `data_file` in the archive
+    /// as the base snapshot recovery from the checkpoint starts on. Nothing may write to the
+    /// page store or the log meanwhile.
+    pub fn checkpoint_with_base(&self, version: VersionId, data_file: &Path) -> StorageResult<LogSequenceNumber> {
+        let archive = self
+            .archive
+            .as_ref()
+            .ok_or_else(|| StorageError::Wal("base snapshots are kept in the WAL archive, which is not configured".to_string()))?;
+        // The checkpoint opens a fresh segment, so no rotation can move it off the LSN the base is stored under
+        self.rotate_file()?;
+        let lsn = self.next_lsn()?;
+        archive.store_base_snapshot(lsn, data_file)?;
+        let written = self.append_durable(&LogEntry::checkpoint(lsn, version))?;
+        if written != lsn {
+            return Err(StorageError::Wal(format!(
+                "checkpoint was written at {written} instead of {lsn}; the log changed while the base was copied"
+            )));
+        }
+        Ok(lsn)
+    }
+
     /// Reads all log entries from all WAL files and applies a callback.
     ///
     /// Steps:
@@ -791,6 +1005,8 @@ mod tests {
             directory: dir.path().to_path_buf(),
             max_file_size: 1024 * 1024,
             direct_io: false,
+            archive: None,
+            group_commit: None,
         };
 
         // Create a new WAL
@@ -832,6 +1048,8 @@ mod tests {
             directory: temp_dir.path().to_path_buf(),
             max_file_size: 1024 * 1024,
             direct_io: false,
+            archive: None,
+            group_commit: None,
         };
 
         // Create a new WAL
@@ -908,6 +1126,8 @@ mod tests {
             directory: temp_dir.path().to_path_buf(),
             max_file_size: 1024, // Small size to trigger rotation
             direct_io: false,
+            archive: None,
+            group_commit: None,
         };
 
         // Create a new WAL
@@ -977,6 +1197,8 @@ mod tests {
             directory: dir.path().to_path_buf(),
             max_file_size: 128,
             direct_io: false,
+            archive: None,
+            group_commit: None,
         };
         let wal = WriteAheadLog::new(wal_config).unwrap();
         // Append a few entries
@@ -1003,6 +1225,26 @@ mod tests {
         assert!(files.len() >= 2);
     }
 
+    #[test]
+    fn test_wal_reopen_appends_to_the_newest_segment() {
+        let dir = tempdir().unwrap();
+        let config = WalConfig {
+            directory: dir.path().to_path_buf(),
+            ..WalConfig::default()
+        };
+        let wal = WriteAheadLog::new(config.clone()).unwrap();
+        wal.append(&LogEntry::begin_transaction(wal.next_lsn().unwrap(), 1)).unwrap();
+        wal.checkpoint().unwrap();
+        let last = wal.append(&LogEntry::begin_transaction(wal.next_lsn().unwrap(), 2)).unwrap();
+        wal.flush().unwrap();
+        drop(wal);
+
+        let wal = WriteAheadLog::new(config).unwrap();
+        let next = wal.append(&LogEntry::begin_transaction(wal.next_lsn().unwrap(), 3)).unwrap();
+        assert_eq!(next.file_id, 1);
+        assert!(next > last);
+    }
+
     #[test]
     fn test_wal_purge_old_files() {
         let dir = tempdir().unwrap();
@@ -1010,6 +1252,8 @@ mod tests {
             directory: dir.path().to_path_buf(),
             max_file_size: 100,
             direct_io: false,
+            archive: None,
+            group_commit: None,
         };
         let wal = WriteAheadLog::new(wal_config).unwrap();
         // Rotate with checkpoint to create multiple files
@@ -1054,6 +1298,8 @@ mod tests {
             directory: dir.path().to_path_buf(),
             max_file_size: 1000,
             direct_io: false,
+            archive: None,
+            group_commit: None,
         };
         let wal = WriteAheadLog::new(wal_config).unwrap();
         // Append a few entries
@@ -1080,4 +1326,109 @@ mod tests {
         .unwrap();
         assert_eq!(count, 5);
     }
+
+    fn group_commit_config(directory: &Path) -> WalConfig {
+        WalConfig {
+            directory: directory.to_path_buf(),
+            max_file_size: 64 * 1024 * 1024,
+            direct_io: false,
+            archive: None,
+            group_commit: Some(GroupCommitConfig {
+                max_wait: std::time::Duration::from_millis(5),
+                max_batch_bytes: 64 * 1024,
+            }),
+        }
+    }
+
+    fn committed_transactions(wal: &WriteAheadLog) -> Vec<u64> {
+        let mut txns = Vec::new();
+        wal.read_records(|entry| {
+            if entry.record_type() == RecordType::Commit {
+                txns.push(entry.transaction_id());
+            }
+            Ok(())
+        })
+        .unwrap();
+        txns.sort();
+        txns
+    }
+
+    #[test]
+    fn test_group_commit_concurrent_commits_are_durable() {
+        let dir = tempdir().unwrap();
+        let wal = Arc::new(WriteAheadLog::new(group_commit_config(dir.path())).unwrap());
+
+        let handles: Vec<_> = (0..8u64)
+            .map(|thread| {
+                let wal = wal.clone();
+                std::thread::spawn(move || {
+                    (0..20u64)
+                        .map(|i| {
+                            let entry = LogEntry::commit_transaction(LogSequenceNumber::default(), thread * 100 + i + 1);
+                            wal.append_durable(&entry).unwrap()
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let mut lsns: Vec<_> = handles.into_iter().flat_map(|h| h.join().unwrap()).map(|lsn| (lsn.file_id, lsn.offset)).collect();
+        lsns.sort();
+        lsns.dedup();
+        assert_eq!(lsns.len(), 160, "every commit gets its own LSN");
+
+        // One fsync per batch, and never more batches than records
+        let stats = wal.group_commit_stats().unwrap();
+        assert_eq!(stats.records, 160);
+        assert_eq!(stats.failed_batches, 0);
+        assert!(stats.batches <= 160);
+        assert_eq!(wal.fsync_count(), stats.batches);
+        assert!(stats.average_batch_size() >= 1.0);
+
+        assert_eq!(committed_transactions(&wal).len(), 160);
+    }
+
+    #[test]
+    fn test_group_commit_crash_between_enqueue_and_flush() {
+        let dir = tempdir().unwrap();
+        let wal = WriteAheadLog::new(group_commit_config(dir.path())).unwrap();
+
+        wal.append_durable(&LogEntry::commit_transaction(LogSequenceNumber::default(), 1)).unwrap();
+
+        // The process "dies" after the record is queued but before the leader writes and syncs it
+        wal.group_commit.as_ref().unwrap().crash_before_flush.store(true, Ordering::SeqCst);
+        let err = wal.append_durable(&LogEntry::commit_transaction(LogSequenceNumber::default(), 2)).unwrap_err();
+        assert!(err.to_string().contains("simulated crash"));
+
+        let stats = wal.group_commit_stats().unwrap();
+        assert_eq!(stats.batches, 1);
+        assert_eq!(stats.failed_batches, 1);
+        drop(wal);
+
+        // Only the acknowledged commit survives a restart
+        let reopened = WriteAheadLog::new(group_commit_config(dir.path())).unwrap();
+        assert_eq!(committed_transactions(&reopened), vec![1]);
+
+        // The log keeps accepting commits after a failed batch
+        reopened.append_durable(&LogEntry::commit_transaction(LogSequenceNumber::default(), 3)).unwrap();
+        assert_eq!(committed_transactions(&reopened), vec![1, 3]);
+    }
+
+    #[test]
+    fn test_durable_append_without_group_commit() {
+        let dir = tempdir().unwrap();
+        let wal = WriteAheadLog::new(WalConfig {
+            group_commit: None,
+            ..group_commit_config(dir.path())
+        })
+        .unwrap();
+
+        for txn in 1..=3 {
+            wal.append_durable(&LogEntry::commit_transaction(LogSequenceNumber::default(), txn)).unwrap();
+        }
+
+        assert!(wal.group_commit_stats().is_none());
+        #[cfg(unix)]
+        assert_eq!(wal.fsync_count(), 3);
+        assert_eq!(committed_transactions(&wal), vec![1, 2, 3]);
+    }
 }
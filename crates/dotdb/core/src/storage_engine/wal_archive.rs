@@ -0,0 +1,732 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+// WAL archiving module
+// This module copies completed WAL segments into an archive directory before they are removed, and replays archived history on top of the last checkpoint for point-in-time recovery. Every archived segment is recorded in a manifest together with its checksum so that recovery can refuse to replay across damaged history.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+
+use crate::storage_engine::lib::{StorageError, StorageResult, calculate_checksum, generate_timestamp};
+use crate::storage_engine::wal::{LogEntry, LogSequenceNumber, RECORD_HEADER_SIZE, RecordHeader, RecordType};
+
+/// Name of the manifest file inside the archive directory
+const MANIFEST_FILE: &str = "archive.manifest";
+
+/// Directory inside the archive holding the page store as it was at each checkpoint
+const BASE_DIR: &str = "base";
+
+/// Compression applied to archived segments
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ArchiveCompression {
+    /// Segments are copied as-is
+    #[default]
+    None,
+    /// Segments are gzip-compressed
+    Gzip,
+}
+
+impl ArchiveCompression {
+    fn extension(&self) -> &'static str {
+        match self {
+            ArchiveCompression::None => "",
+            ArchiveCompression::Gzip => ".gz",
+        }
+    }
+}
+
+/// Configuration for WAL archiving
+#[derive(Debug, Clone)]
+pub struct WalArchiveConfig {
+    /// Directory archived segments are written to
+    pub directory: PathBuf,
+    /// Compression applied to archived segments
+    pub compression: ArchiveCompression,
+}
+
+/// Manifest entry describing one archived segment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedSegment {
+    /// WAL file ID of the segment
+    pub file_id: u32,
+    /// File name inside the archive directory
+    pub file_name: String,
+    /// Uncompressed size in bytes
+    pub size: u64,
+    /// Checksum of the uncompressed segment
+    pub checksum: u32,
+    /// Compression used for the archived copy
+    pub compression: ArchiveCompression,
+    /// When the segment was archived
+    pub archived_at: u64,
+}
+
+/// WalArchive stores completed WAL segments and the manifest describing them.
+pub struct WalArchive {
+    /// Archive configuration
+    config: WalArchiveConfig,
+    /// Archived segments keyed by file ID
+    manifest: Mutex<BTreeMap<u32, ArchivedSegment>>,
+}
+
+impl WalArchive {
+    /// Open an archive directory, creating it if needed and loading its manifest
+    pub fn open(config: WalArchiveConfig) -> StorageResult<Self> {
+        fs::create_dir_all(&config.directory)?;
+
+        let manifest_path = config.directory.join(MANIFEST_FILE);
+        let segments: Vec<ArchivedSegment> = if manifest_path.exists() {
+            let data = fs::read(&manifest_path)?;
+            serde_json::from_slice(&data).map_err(|e| StorageError::Corruption(format!("Invalid WAL archive manifest: {e}")))?
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            config,
+            manifest: Mutex::new(segments.into_iter().map(|s| (s.file_id, s)).collect()),
+        })
+    }
+
+    /// Get the archive configuration
+    pub fn config(&self) -> &WalArchiveConfig {
+        &self.config
+    }
+
+    /// Check whether a segment has already been archived
+    pub fn is_archived(&self, file_id: u32) -> bool {
+        self.manifest.lock().unwrap().contains_key(&file_id)
+    }
+
+    /// All archived segments, ordered by file ID
+    pub fn segments(&self) -> Vec<ArchivedSegment> {
+        self.manifest.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Copies a completed segment into the archive.
+    ///
+    /// Steps:
+    /// 1. Read the segment and compute its checksum.
+    /// 2. Write the (optionally compressed) copy to a temporary file and rename it into place.
+    /// 3. Record the segment in the manifest and persist the manifest.
+    ///
+    /// Archiving a segment that is already in the manifest returns the existing entry.
+    pub fn archive_segment(&self, file_id: u32, path: &Path) -> StorageResult<ArchivedSegment> {
+        let mut manifest = self.manifest.lock().unwrap();
+        if let Some(existing) = manifest.get(&file_id) {
+            return Ok(existing.clone());
+        }
+
+        let data = fs::read(path)?;
+        let compression = self.config.compression;
+        let file_name = format!("wal.{file_id:04}{}", compression.extension());
+
+        let encoded = match compression {
+            ArchiveCompression::None => data.clone(),
+            ArchiveCompression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&data)?;
+                encoder.finish()?
+            }
+        };
+        write_atomically(&self.config.directory.join(&file_name), &encoded)?;
+
+        let segment = ArchivedSegment {
+            file_id,
+            file_name,
+            size: data.len() as u64,
+            checksum: calculate_checksum(&data),
+            compression,
+            archived_at: generate_timestamp(),
+        };
+        manifest.insert(file_id, segment.clone());
+
+        let entries: Vec<&ArchivedSegment> = manifest.values().collect();
+        let manifest_data = serde_json::to_vec_pretty(&entries).map_err(|e| StorageError::Wal(e.to_string()))?;
+        write_atomically(&self.config.directory.join(MANIFEST_FILE), &manifest_data)?;
+
+        Ok(segment)
+    }
+
+    /// Copies the page store at `data_file` into the archive as the base that replay from
+    /// `checkpoint` starts on. Nothing may write to the page store while it is copied.
+    pub fn store_base_snapshot(&self, checkpoint: LogSequenceNumber, data_file: &Path) -> StorageResult<PathBuf> {
+        let path = self.base_snapshot_path(checkpoint);
+        fs::create_dir_all(self.config.directory.join(BASE_DIR))?;
+        let tmp_path = path.with_extension("tmp");
+        fs::copy(data_file, &tmp_path)?;
+        fs::File::open(&tmp_path)?.sync_all()?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(path)
+    }
+
+    /// The page store as it was at `checkpoint`, if a base snapshot was stored for it
+    pub fn base_snapshot(&self, checkpoint: LogSequenceNumber) -> Option<PathBuf> {
+        Some(self.base_snapshot_path(checkpoint)).filter(|path| path.is_file())
+    }
+
+    fn base_snapshot_path(&self, checkpoint: LogSequenceNumber) -> PathBuf {
+        self.config.directory.join(BASE_DIR).join(format!("{:04}-{}.db", checkpoint.file_id, checkpoint.offset))
+    }
+
+    /// Read and decompress an archived segment. The checksum is not verified here.
+    pub fn read_segment(&self, segment: &ArchivedSegment) -> StorageResult<Vec<u8>> {
+        let raw = fs::read(self.config.directory.join(&segment.file_name))?;
+        match segment.compression {
+            ArchiveCompression::None => Ok(raw),
+            ArchiveCompression::Gzip => {
+                let mut data = Vec::with_capacity(segment.size as usize);
+                GzDecoder::new(raw.as_slice())
+                    .read_to_end(&mut data)
+                    .map_err(|e| StorageError::Corruption(format!("Failed to decompress archived segment {}: {e}", segment.file_name)))?;
+                Ok(data)
+            }
+        }
+    }
+}
+
+/// Write a file through a temporary sibling so readers never see a partial file
+fn write_atomically(path: &Path, data: &[u8]) -> StorageResult<()> {
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Point in history that recovery replays up to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryTarget {
+    /// Replay every record up to and including this LSN
+    Lsn(LogSequenceNumber),
+    /// Replay transactions committed at or before this time (nanoseconds since the Unix epoch)
+    Timestamp(u64),
+}
+
+/// Outcome of a point-in-time recovery
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecoveryReport {
+    /// Checkpoint the replay started from; `None` if replay started at the beginning of the log
+    pub checkpoint_lsn: Option<LogSequenceNumber>,
+    /// LSN of the last record handed to the apply callback
+    pub last_applied_lsn: Option<LogSequenceNumber>,
+    /// Number of committed transactions replayed
+    pub transactions_applied: usize,
+    /// Number of transactions that aborted or had not committed at the target
+    pub transactions_discarded: usize,
+    /// Number of segments read
+    pub segments_replayed: usize,
+}
+
+/// A segment to replay and what is known about its integrity
+struct SegmentSource {
+    file_id: u32,
+    data: Vec<u8>,
+    /// Manifest checksum, for archived segments
+    expected_checksum: Option<u32>,
+    /// Whether the segment may end in a partially written record (the live tail)
+    is_tail: bool,
+}
+
+/// First damaged position inside a segment
+pub(crate) struct SegmentDamage {
+    offset: u64,
+    reason: String,
+}
+
+/// Parses every record of a segment, validating each record checksum.
+///
+/// A partial record at the end is only tolerated for the live tail segment, where it is the
+/// normal result of a crash during append.
+pub(crate) fn parse_segment(file_id: u32, data: &[u8], is_tail: bool) -> Result<Vec<LogEntry>, SegmentDamage> {
+    let mut entries = Vec::new();
+    let mut offset = 0usize;
+
+    while offset < data.len() {
+        let damage = |reason: String| SegmentDamage { offset: offset as u64, reason };
+
+        if offset + RECORD_HEADER_SIZE > data.len() {
+            if is_tail {
+                break;
+            }
+            return Err(damage("truncated record header".to_string()));
+        }
+
+        let header = RecordHeader::deserialize(&data[offset..offset + RECORD_HEADER_SIZE]).map_err(|e| damage(e.to_string()))?;
+        let data_end = offset + RECORD_HEADER_SIZE + header.data_length as usize;
+        if data_end > data.len() {
+            if is_tail {
+                break;
+            }
+            return Err(damage("truncated record data".to_string()));
+        }
+
+        let expected_lsn = LogSequenceNumber { file_id, offset: offset as u64 };
+        if header.lsn != expected_lsn {
+            return Err(damage(format!("record claims LSN {}", header.lsn)));
+        }
+
+        let entry = LogEntry {
+            header,
+            data: data[offset + RECORD_HEADER_SIZE..data_end].to_vec(),
+        };
+        if !entry.is_valid() {
+            return Err(damage("record checksum mismatch".to_string()));
+        }
+
+        entries.push(entry);
+        offset = data_end;
+    }
+
+    Ok(entries)
+}
+
+/// Loads, in file ID order, the archived segments followed by live segments not yet archived
+fn collect_segments(archive: Option<&WalArchive>, wal_dir: &Path) -> StorageResult<Vec<SegmentSource>> {
+    let mut sources = BTreeMap::new();
+
+    if let Some(archive) = archive {
+        for segment in archive.segments() {
+            let data = archive.read_segment(&segment).map_err(|e| StorageError::UnrecoverableWal {
+                from: LogSequenceNumber {
+                    file_id: segment.file_id,
+                    offset: 0,
+                },
+                to: LogSequenceNumber {
+                    file_id: segment.file_id,
+                    offset: segment.size,
+                },
+                reason: format!("archived segment {} is unreadable: {e}", segment.file_name),
+            })?;
+            sources.insert(
+                segment.file_id,
+                SegmentSource {
+                    file_id: segment.file_id,
+                    data,
+                    expected_checksum: Some(segment.checksum),
+                    is_tail: false,
+                },
+            );
+        }
+    }
+
+    if wal_dir.exists() {
+        for entry in fs::read_dir(wal_dir)? {
+            let path = entry?.path();
+            if path.is_file()
+                && let Some(fname) = path.file_name().and_then(|f| f.to_str())
+                && let Some(id_str) = fname.strip_prefix("wal.")
+                && let Ok(id) = id_str.parse::<u32>()
+                && !sources.contains_key(&id)
+            {
+                sources.insert(
+                    id,
+                    SegmentSource {
+                        file_id: id,
+                        data: fs::read(&path)?,
+                        expected_checksum: None,
+                        is_tail: false,
+                    },
+                );
+            }
+        }
+    }
+
+    // Only the newest live segment can still be in the middle of an append
+    if let Some(last) = sources.values_mut().next_back()
+        && last.expected_checksum.is_none()
+    {
+        last.is_tail = true;
+    }
+
+    Ok(sources.into_values().collect())
+}
+
+/// Replays segments from the last checkpoint at or before `target`.
+///
+/// Steps:
+/// 1. Load archived and live segments in order, refusing gaps in the file ID sequence.
+/// 2. Validate each segment checksum against the manifest and every record checksum.
+/// 3. Collect records up to the target, restarting the collection at every checkpoint.
+/// 4. Let `restore_base` restore the data files to the checkpoint replay starts from.
+/// 5. Hand the records of each committed transaction to `apply` in log order.
+///
+/// Nothing is restored or applied if any segment before the target is damaged; the error
+/// names the LSN range that cannot be recovered.
+pub(crate) fn recover<R, F>(archive: Option<&WalArchive>, wal_dir: &Path, target: RecoveryTarget, restore_base: R, mut apply: F) -> StorageResult<RecoveryReport>
+where
+    R: FnOnce(Option<LogSequenceNumber>) -> StorageResult<()>,
+    F: FnMut(&LogEntry) -> StorageResult<()>,
+{
+    let sources = collect_segments(archive, wal_dir)?;
+    let mut report = RecoveryReport::default();
+    let mut pending: Vec<LogEntry> = Vec::new();
+    let mut expected_id: Option<u32> = None;
+
+    'segments: for source in sources {
+        if let Some(expected) = expected_id
+            && source.file_id != expected
+        {
+            return Err(StorageError::UnrecoverableWal {
+                from: LogSequenceNumber { file_id: expected, offset: 0 },
+                to: LogSequenceNumber { file_id: source.file_id, offset: 0 },
+                reason: format!("segments {}..{} are missing from the archive", expected, source.file_id),
+            });
+        }
+        expected_id = Some(source.file_id + 1);
+
+        let segment_end = LogSequenceNumber {
+            file_id: source.file_id,
+            offset: source.data.len() as u64,
+        };
+        let entries = match parse_segment(source.file_id, &source.data, source.is_tail) {
+            Ok(entries) => entries,
+            Err(damage) => {
+                return Err(StorageError::UnrecoverableWal {
+                    from: LogSequenceNumber {
+                        file_id: source.file_id,
+                        offset: damage.offset,
+                    },
+                    to: segment_end,
+                    reason: damage.reason,
+                });
+            }
+        };
+
+        // Records can all parse while the segment is still damaged, e.g. trailing bytes
+        if let Some(expected) = source.expected_checksum
+            && calculate_checksum(&source.data) != expected
+        {
+            let valid_end = entries.last().map_or(0, |e| e.lsn().offset + e.serialized_size() as u64);
+            return Err(StorageError::UnrecoverableWal {
+                from: LogSequenceNumber {
+                    file_id: source.file_id,
+                    offset: valid_end,
+                },
+                to: segment_end,
+                reason: "segment checksum does not match the archive manifest".to_string(),
+            });
+        }
+
+        report.segments_replayed += 1;
+
+        for entry in entries {
+            let within_target = match target {
+                RecoveryTarget::Lsn(lsn) => entry.lsn() <= lsn,
+                RecoveryTarget::Timestamp(ts) => entry.commit_timestamp().is_none_or(|committed| committed <= ts),
+            };
+            if !within_target {
+                break 'segments;
+            }
+
+            if entry.record_type() == RecordType::Checkpoint {
+                report.checkpoint_lsn = Some(entry.lsn());
+                pending.clear();
+            } else {
+                pending.push(entry);
+            }
+        }
+    }
+
+    restore_base(report.checkpoint_lsn)?;
+
+    // Buffer each transaction until its commit so that only committed work is applied
+    let mut transactions: HashMap<u64, Vec<LogEntry>> = HashMap::new();
+    for entry in pending {
+        match entry.record_type() {
+            RecordType::Begin => {
+                transactions.entry(entry.transaction_id()).or_default();
+            }
+            RecordType::Commit => {
+                for record in transactions.remove(&entry.transaction_id()).unwrap_or_default() {
+                    apply(&record)?;
+                    report.last_applied_lsn = Some(record.lsn());
+                }
+                apply(&entry)?;
+                report.last_applied_lsn = Some(entry.lsn());
+                report.transactions_applied += 1;
+            }
+            RecordType::Abort => {
+                transactions.remove(&entry.transaction_id());
+                report.transactions_discarded += 1;
+            }
+            RecordType::Read | RecordType::Checkpoint => {}
+            RecordType::Write | RecordType::Allocate | RecordType::Free => {
+                transactions.entry(entry.transaction_id()).or_default().push(entry);
+            }
+        }
+    }
+    report.transactions_discarded += transactions.len();
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage_engine::file_format::{Page, PageHeader, PageId, PageType};
+    use crate::storage_engine::lib::VersionId;
+    use crate::storage_engine::wal::{WalConfig, WriteAheadLog};
+    use tempfile::tempdir;
+
+    fn archived_wal(dir: &Path, compression: ArchiveCompression) -> WriteAheadLog {
+        WriteAheadLog::new(WalConfig {
+            directory: dir.join("wal"),
+            max_file_size: 1024 * 1024,
+            direct_io: false,
+            archive: Some(WalArchiveConfig {
+                directory: dir.join("archive"),
+                compression,
+            }),
+            group_commit: None,
+        })
+        .unwrap()
+    }
+
+    fn write_transaction(wal: &WriteAheadLog, txn_id: u64, page_id: u64, fill: u8) -> LogSequenceNumber {
+        let mut page = Page {
+            id: PageId(page_id),
+            header: PageHeader::new(PageType::Data, VersionId(txn_id)),
+            data: vec![fill; 64],
+        };
+        page.header.data_size = 64;
+        page.update_checksum();
+
+        wal.append(&LogEntry::begin_transaction(wal.next_lsn().unwrap(), txn_id)).unwrap();
+        wal.append(&LogEntry::write_page(wal.next_lsn().unwrap(), txn_id, &page)).unwrap();
+        wal.append(&LogEntry::commit_transaction(wal.next_lsn().unwrap(), txn_id)).unwrap()
+    }
+
+    fn replayed_transactions(report: StorageResult<RecoveryReport>, applied: &[LogEntry]) -> Vec<u64> {
+        report.unwrap();
+        applied.iter().filter(|e| e.record_type() == RecordType::Commit).map(|e| e.transaction_id()).collect()
+    }
+
+    #[test]
+    fn test_completed_segments_are_archived() {
+        let dir = tempdir().unwrap();
+        let wal = archived_wal(dir.path(), ArchiveCompression::Gzip);
+
+        write_transaction(&wal, 1, 1, 0xAA);
+        wal.checkpoint().unwrap();
+        write_transaction(&wal, 2, 2, 0xBB);
+        wal.checkpoint().unwrap();
+
+        let archive = wal.archive().unwrap();
+        let segments = archive.segments();
+        assert_eq!(segments.iter().map(|s| s.file_id).collect::<Vec<_>>(), vec![0, 1]);
+        assert!(segments[0].file_name.ends_with(".gz"));
+
+        let original = std::fs::read(dir.path().join("wal").join("wal.0000")).unwrap();
+        assert_eq!(archive.read_segment(&segments[0]).unwrap(), original);
+        assert_eq!(calculate_checksum(&original), segments[0].checksum);
+
+        // Purging removes the live file but the archived copy survives
+        wal.purge_old_files(2).unwrap();
+        assert!(!dir.path().join("wal").join("wal.0000").exists());
+        let reopened = WalArchive::open(archive.config().clone()).unwrap();
+        assert_eq!(reopened.segments(), segments);
+    }
+
+    #[test]
+    fn test_recover_to_lsn() {
+        let dir = tempdir().unwrap();
+        let wal = archived_wal(dir.path(), ArchiveCompression::None);
+
+        write_transaction(&wal, 1, 1, 1);
+        wal.checkpoint().unwrap();
+        let second_commit = write_transaction(&wal, 2, 2, 2);
+        write_transaction(&wal, 3, 3, 3);
+        wal.purge_old_files(1).unwrap();
+
+        let mut applied = Vec::new();
+        let report = wal.recover_to_lsn(second_commit, |e| {
+            applied.push(e.clone());
+            Ok(())
+        });
+        assert_eq!(replayed_transactions(report, &applied), vec![1, 2]);
+
+        let page = applied.iter().find_map(|e| e.page().filter(|p| p.id == PageId(2))).unwrap();
+        assert_eq!(page.data, vec![2; 64]);
+    }
+
+    #[test]
+    fn test_recover_to_timestamp_starts_at_last_checkpoint() {
+        let dir = tempdir().unwrap();
+        let wal = archived_wal(dir.path(), ArchiveCompression::Gzip);
+
+        write_transaction(&wal, 1, 1, 1);
+        let checkpoint = wal.append(&LogEntry::checkpoint(wal.next_lsn().unwrap(), VersionId(1))).unwrap();
+        wal.checkpoint().unwrap();
+        write_transaction(&wal, 2, 2, 2);
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        let cutoff = generate_timestamp();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        write_transaction(&wal, 3, 3, 3);
+
+        let mut applied = Vec::new();
+        let report = wal
+            .recover_to_timestamp(cutoff, |e| {
+                applied.push(e.clone());
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(report.checkpoint_lsn, Some(checkpoint));
+        assert_eq!(report.transactions_applied, 1);
+        // Transaction 3 began before the cutoff but committed after it
+        assert_eq!(report.transactions_discarded, 1);
+        assert_eq!(replayed_transactions(Ok(report), &applied), vec![2]);
+    }
+
+    #[test]
+    fn test_restore_starts_from_the_base_stored_at_the_checkpoint() {
+        let dir = tempdir().unwrap();
+        let wal = archived_wal(dir.path(), ArchiveCompression::None);
+        let data_file = dir.path().join("storage.db");
+
+        write_transaction(&wal, 1, 1, 1);
+        fs::write(&data_file, b"pages of transaction 1").unwrap();
+        let checkpoint = wal.checkpoint_with_base(VersionId(1), &data_file).unwrap();
+        assert_eq!(checkpoint.offset, 0);
+        write_transaction(&wal, 2, 2, 2);
+        let target = write_transaction(&wal, 3, 3, 3);
+        fs::write(&data_file, b"pages of transactions 1 to 3").unwrap();
+
+        let archive = wal.archive().unwrap();
+        let restored = std::cell::RefCell::new(None);
+        let mut applied = Vec::new();
+        let report = wal.restore_to(
+            RecoveryTarget::Lsn(target),
+            |from| {
+                let base = archive.base_snapshot(from.unwrap()).unwrap();
+                *restored.borrow_mut() = Some(fs::read(base).unwrap());
+                Ok(())
+            },
+            |e| {
+                assert!(restored.borrow().is_some(), "records are applied after the base is restored");
+                applied.push(e.clone());
+                Ok(())
+            },
+        );
+
+        assert_eq!(report.as_ref().unwrap().checkpoint_lsn, Some(checkpoint));
+        assert_eq!(restored.into_inner().unwrap(), b"pages of transaction 1");
+        assert_eq!(replayed_transactions(report, &applied), vec![2, 3]);
+        assert!(archive.base_snapshot(LogSequenceNumber { file_id: 0, offset: 0 }).is_none());
+    }
+
+    #[test]
+    fn test_recovery_refuses_corrupted_segment() {
+        let dir = tempdir().unwrap();
+        let wal = archived_wal(dir.path(), ArchiveCompression::None);
+
+        write_transaction(&wal, 1, 1, 1);
+        wal.checkpoint().unwrap();
+        write_transaction(&wal, 2, 2, 2);
+        wal.checkpoint().unwrap();
+        let last = write_transaction(&wal, 3, 3, 3);
+        wal.purge_old_files(2).unwrap();
+
+        // Flip a byte inside the page write record of the archived segment 1
+        let segment = wal.archive().unwrap().segments()[1].clone();
+        let path = dir.path().join("archive").join(&segment.file_name);
+        let mut bytes = std::fs::read(&path).unwrap();
+        let write_offset = RECORD_HEADER_SIZE;
+        bytes[write_offset + RECORD_HEADER_SIZE + 20] ^= 0xFF;
+        std::fs::write(&path, &bytes).unwrap();
+
+        let mut applied = 0;
+        let err = wal
+            .recover_to_lsn(last, |_| {
+                applied += 1;
+                Ok(())
+            })
+            .unwrap_err();
+
+        match err {
+            StorageError::UnrecoverableWal { from, to, .. } => {
+                assert_eq!(from, LogSequenceNumber { file_id: 1, offset: write_offset as u64 });
+                assert_eq!(to, LogSequenceNumber { file_id: 1, offset: segment.size });
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+        assert_eq!(applied, 0);
+    }
+
+    #[test]
+    fn test_recovery_reports_missing_segment() {
+        let dir = tempdir().unwrap();
+        let wal = archived_wal(dir.path(), ArchiveCompression::None);
+
+        for txn in 1..=3 {
+            write_transaction(&wal, txn, txn, txn as u8);
+            wal.checkpoint().unwrap();
+        }
+        wal.purge_old_files(3).unwrap();
+
+        let segment = wal.archive().unwrap().segments()[1].clone();
+        std::fs::remove_file(dir.path().join("archive").join(&segment.file_name)).unwrap();
+
+        let err = wal.recover_to_lsn(wal.current_lsn(), |_| Ok(())).unwrap_err();
+        match err {
+            StorageError::UnrecoverableWal { from, to, .. } => {
+                assert_eq!(from, LogSequenceNumber { file_id: 1, offset: 0 });
+                assert_eq!(to, LogSequenceNumber { file_id: 1, offset: segment.size });
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+
+    #[test]
+    fn test_recovery_reports_gap_in_segments() {
+        let dir = tempdir().unwrap();
+        let wal = WriteAheadLog::new(WalConfig {
+            directory: dir.path().to_path_buf(),
+            max_file_size: 1024 * 1024,
+            direct_io: false,
+            archive: None,
+            group_commit: None,
+        })
+        .unwrap();
+
+        for txn in 1..=3 {
+            write_transaction(&wal, txn, txn, txn as u8);
+            wal.checkpoint().unwrap();
+        }
+        std::fs::remove_file(dir.path().join("wal.0001")).unwrap();
+
+        let err = wal.recover_to_lsn(wal.current_lsn(), |_| Ok(())).unwrap_err();
+        match err {
+            StorageError::UnrecoverableWal { from, to, .. } => {
+                assert_eq!(from, LogSequenceNumber { file_id: 1, offset: 0 });
+                assert_eq!(to, LogSequenceNumber { file_id: 2, offset: 0 });
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+}
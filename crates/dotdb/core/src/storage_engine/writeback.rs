@@ -0,0 +1,406 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+// Background writeback module
+// Dirty pages are queued to a pool of writer threads instead of being written by the threads that dirtied them. Writing starts once the dirty page count reaches the start watermark; between the start watermark and the hard limit commits are delayed in proportion to how close the pool is to the limit, and at the hard limit they wait for the writers to catch up.
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, RwLock};
+use std::time::{Duration, Instant};
+
+use tracing::warn;
+
+use crate::storage_engine::buffer_manager::BufferPool;
+use crate::storage_engine::file_format::PageId;
+
+/// Fraction of `max_dirty_pages` at which the writers start flushing
+pub const FLUSH_START_RATIO: f64 = 0.60;
+/// Fraction of `max_dirty_pages` at which commits wait for the writers
+pub const FLUSH_HARD_LIMIT_RATIO: f64 = 0.95;
+/// Delay of a commit just below the hard limit
+pub const MAX_COMMIT_DELAY: Duration = Duration::from_millis(10);
+/// Pages a writer takes from its queue at a time
+const WRITER_BATCH: usize = 32;
+
+/// How a commit is slowed down by the number of dirty pages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backpressure {
+    /// Below the start watermark, the commit proceeds at once
+    None,
+    /// Between the watermarks, the commit is delayed
+    Delay(Duration),
+    /// At the hard limit, the commit waits until the writers bring the count back under it
+    Block,
+}
+
+impl Backpressure {
+    /// Backpressure with `dirty` of at most `max_dirty` pages dirty
+    ///
+    /// The delay grows linearly from zero at the start watermark to `MAX_COMMIT_DELAY`
+    /// at the hard limit.
+    pub fn for_dirty_pages(dirty: usize, max_dirty: usize) -> Self {
+        if dirty < start_watermark(max_dirty) {
+            return Self::None;
+        }
+        if dirty >= hard_limit(max_dirty) {
+            return Self::Block;
+        }
+        let ratio = dirty as f64 / max_dirty.max(1) as f64;
+        let pressure = ((ratio - FLUSH_START_RATIO) / (FLUSH_HARD_LIMIT_RATIO - FLUSH_START_RATIO)).clamp(0.0, 1.0);
+        Self::Delay(MAX_COMMIT_DELAY.mul_f64(pressure))
+    }
+}
+
+/// Dirty page count at which the writers start flushing
+pub fn start_watermark(max_dirty: usize) -> usize {
+    ((max_dirty as f64 * FLUSH_START_RATIO).ceil() as usize).max(1)
+}
+
+/// Dirty page count at which commits wait for the writers
+pub fn hard_limit(max_dirty: usize) -> usize {
+    ((max_dirty as f64 * FLUSH_HARD_LIMIT_RATIO).ceil() as usize).max(1)
+}
+
+/// Throughput of one background writer thread
+#[derive(Debug, Clone, PartialEq)]
+pub struct WriterThroughput {
+    /// Index of the writer thread
+    pub writer: usize,
+    /// Pages written back
+    pub pages_written: u64,
+    /// Bytes written back
+    pub bytes_written: u64,
+    /// Page writes that failed; the pages stay dirty
+    pub write_errors: u64,
+    /// Time spent writing
+    pub busy: Duration,
+    /// Time since the writer started
+    pub uptime: Duration,
+}
+
+impl WriterThroughput {
+    /// Pages written per second of writing
+    pub fn pages_per_sec(&self) -> f64 {
+        let busy = self.busy.as_secs_f64();
+        if busy == 0.0 { 0.0 } else { self.pages_written as f64 / busy }
+    }
+
+    /// Fraction of its uptime the writer spent writing
+    pub fn utilization(&self) -> f64 {
+        let uptime = self.uptime.as_secs_f64();
+        if uptime == 0.0 { 0.0 } else { (self.busy.as_secs_f64() / uptime).min(1.0) }
+    }
+}
+
+/// Counters of one writer thread
+struct WriterStats {
+    pages_written: AtomicU64,
+    bytes_written: AtomicU64,
+    write_errors: AtomicU64,
+    busy_nanos: AtomicU64,
+}
+
+impl WriterStats {
+    fn new() -> Self {
+        Self {
+            pages_written: AtomicU64::new(0),
+            bytes_written: AtomicU64::new(0),
+            write_errors: AtomicU64::new(0),
+            busy_nanos: AtomicU64::new(0),
+        }
+    }
+}
+
+#[derive(Default)]
+struct QueueState {
+    /// Pages waiting for each writer
+    queues: Vec<VecDeque<PageId>>,
+    /// Pages waiting in any queue
+    queued: HashSet<PageId>,
+    /// Batches taken by writers and not yet finished
+    in_flight: usize,
+    /// Writers exit once their queue is empty
+    shutdown: bool,
+}
+
+impl QueueState {
+    fn is_idle(&self) -> bool {
+        self.in_flight == 0 && self.queued.is_empty()
+    }
+}
+
+/// Dirty pages waiting for the background writers, and the dirty page count commits are throttled on
+pub struct WritebackQueue {
+    state: Mutex<QueueState>,
+    /// Wakes writers when pages are queued or on shutdown
+    work: Condvar,
+    /// Wakes committers and `close` when writers make progress
+    progress: Condvar,
+    dirty_pages: AtomicUsize,
+    max_dirty_pages: AtomicUsize,
+    writers: Vec<WriterStats>,
+    started: Instant,
+}
+
+impl WritebackQueue {
+    /// Create a queue for `writers` writer threads; each page always goes to the same writer
+    pub fn new(writers: usize, max_dirty_pages: usize) -> Self {
+        let writers = writers.max(1);
+        Self {
+            state: Mutex::new(QueueState {
+                queues: (0..writers).map(|_| VecDeque::new()).collect(),
+                ..QueueState::default()
+            }),
+            work: Condvar::new(),
+            progress: Condvar::new(),
+            dirty_pages: AtomicUsize::new(0),
+            max_dirty_pages: AtomicUsize::new(max_dirty_pages),
+            writers: (0..writers).map(|_| WriterStats::new()).collect(),
+            started: Instant::now(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Number of writer threads the queue feeds
+    pub fn writer_count(&self) -> usize {
+        self.writers.len()
+    }
+
+    /// Number of dirty pages in the buffer pool
+    pub fn dirty_pages(&self) -> usize {
+        self.dirty_pages.load(Ordering::Acquire)
+    }
+
+    /// Maximum number of dirty pages
+    pub fn max_dirty_pages(&self) -> usize {
+        self.max_dirty_pages.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn set_max_dirty_pages(&self, max_dirty_pages: usize) {
+        self.max_dirty_pages.store(max_dirty_pages, Ordering::Release);
+        self.progress.notify_all();
+    }
+
+    pub(crate) fn set_dirty_pages(&self, dirty_pages: usize) {
+        if self.dirty_pages.swap(dirty_pages, Ordering::AcqRel) > dirty_pages {
+            // Taking the lock orders the update before any committer's check-then-wait
+            drop(self.lock());
+            self.progress.notify_all();
+        }
+    }
+
+    /// Backpressure a commit is under right now; none once the writers are shut down
+    pub fn backpressure(&self) -> Backpressure {
+        if self.lock().shutdown {
+            return Backpressure::None;
+        }
+        Backpressure::for_dirty_pages(self.dirty_pages(), self.max_dirty_pages())
+    }
+
+    /// Queue pages for writeback, skipping those already queued
+    pub fn enqueue(&self, pages: impl IntoIterator<Item = PageId>) {
+        let mut state = self.lock();
+        let writers = state.queues.len() as u64;
+        let mut added = false;
+        for page_id in pages {
+            if state.queued.insert(page_id) {
+                state.queues[(page_id.0 % writers) as usize].push_back(page_id);
+                added = true;
+            }
+        }
+        if added {
+            self.work.notify_all();
+        }
+    }
+
+    /// Number of pages waiting for a writer
+    pub fn queued(&self) -> usize {
+        self.lock().queued.len()
+    }
+
+    /// Wait until the writer has pages to write; `None` once it should exit
+    fn next_batch(&self, writer: usize) -> Option<Vec<PageId>> {
+        let mut state = self.lock();
+        loop {
+            if !state.queues[writer].is_empty() {
+                let take = state.queues[writer].len().min(WRITER_BATCH);
+                let batch: Vec<PageId> = state.queues[writer].drain(..take).collect();
+                for page_id in &batch {
+                    state.queued.remove(page_id);
+                }
+                state.in_flight += 1;
+                return Some(batch);
+            }
+            if state.shutdown {
+                return None;
+            }
+            state = self.work.wait(state).unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+
+    fn finish_batch(&self) {
+        let mut state = self.lock();
+        state.in_flight -= 1;
+        drop(state);
+        self.progress.notify_all();
+    }
+
+    /// Wait until the dirty page count is under the hard limit, or `timeout` passes
+    ///
+    /// Returns whether the count is under the limit.
+    pub fn wait_below_hard_limit(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.lock();
+        loop {
+            if state.shutdown || self.dirty_pages() < hard_limit(self.max_dirty_pages()) {
+                return true;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            state = self.progress.wait_timeout(state, deadline - now).unwrap_or_else(|poisoned| poisoned.into_inner()).0;
+        }
+    }
+
+    /// Wait until every queued page has been written
+    pub fn wait_idle(&self) {
+        let mut state = self.lock();
+        while !state.is_idle() {
+            state = self.progress.wait(state).unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+
+    /// Let the writers exit once they have written everything queued
+    pub fn shutdown(&self) {
+        self.lock().shutdown = true;
+        self.work.notify_all();
+        self.progress.notify_all();
+    }
+
+    /// Throughput of each writer thread
+    pub fn writer_stats(&self) -> Vec<WriterThroughput> {
+        let uptime = self.started.elapsed();
+        self.writers
+            .iter()
+            .enumerate()
+            .map(|(writer, stats)| WriterThroughput {
+                writer,
+                pages_written: stats.pages_written.load(Ordering::Relaxed),
+                bytes_written: stats.bytes_written.load(Ordering::Relaxed),
+                write_errors: stats.write_errors.load(Ordering::Relaxed),
+                busy: Duration::from_nanos(stats.busy_nanos.load(Ordering::Relaxed)),
+                uptime,
+            })
+            .collect()
+    }
+}
+
+/// Body of writer thread `writer`: write back the pages queued to it until shut down
+///
+/// Each page is copied under the pool lock and written with the storage file locked. The
+/// pool lock is released before the write, so foreground readers and writers of resident
+/// pages are not held up by the I/O; the file lock is taken before the pool lock is
+/// released, so a later flush of the same page cannot reach the disk first.
+pub(crate) fn run_writer(writer: usize, pool: Arc<RwLock<BufferPool>>, queue: Arc<WritebackQueue>) {
+    let file_format = match pool.read() {
+        Ok(pool) => pool.file_format(),
+        Err(_) => return,
+    };
+    let stats = &queue.writers[writer];
+
+    while let Some(batch) = queue.next_batch(writer) {
+        for page_id in batch {
+            let Ok(guard) = pool.write() else {
+                break;
+            };
+            let Some((mut page, modifications)) = guard.begin_writeback(page_id) else {
+                continue;
+            };
+            let Ok(mut file) = file_format.lock() else {
+                break;
+            };
+            drop(guard);
+
+            let started = Instant::now();
+            let bytes = file.page_size() as u64;
+            page.update_checksum();
+            let result = file.write_page(&mut page);
+            drop(file);
+            stats.busy_nanos.fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+
+            match result {
+                Ok(()) => {
+                    stats.pages_written.fetch_add(1, Ordering::Relaxed);
+                    stats.bytes_written.fetch_add(bytes, Ordering::Relaxed);
+                    if let Ok(mut pool) = pool.write() {
+                        pool.finish_writeback(page_id, modifications);
+                    }
+                }
+                Err(e) => {
+                    stats.write_errors.fetch_add(1, Ordering::Relaxed);
+                    warn!(writer, page = page_id.0, error = %e, "Background writeback failed, page stays dirty");
+                }
+            }
+        }
+        queue.finish_batch();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backpressure_grows_between_watermarks() {
+        assert_eq!(start_watermark(1000), 600);
+        assert_eq!(hard_limit(1000), 950);
+
+        assert_eq!(Backpressure::for_dirty_pages(0, 1000), Backpressure::None);
+        assert_eq!(Backpressure::for_dirty_pages(599, 1000), Backpressure::None);
+        assert_eq!(Backpressure::for_dirty_pages(600, 1000), Backpressure::Delay(Duration::ZERO));
+        assert_eq!(Backpressure::for_dirty_pages(950, 1000), Backpressure::Block);
+        assert_eq!(Backpressure::for_dirty_pages(2000, 1000), Backpressure::Block);
+
+        let delay = |dirty| match Backpressure::for_dirty_pages(dirty, 1000) {
+            Backpressure::Delay(delay) => delay,
+            other => panic!("expected a delay, got {other:?}"),
+        };
+        assert!(delay(700) < delay(800));
+        assert!(delay(800) < delay(949));
+        assert!(delay(949) <= MAX_COMMIT_DELAY);
+    }
+
+    #[test]
+    fn test_pages_stick_to_one_writer_and_are_queued_once() {
+        let queue = WritebackQueue::new(2, 10);
+        queue.enqueue([PageId(1), PageId(2), PageId(3), PageId(1)]);
+        assert_eq!(queue.queued(), 3);
+
+        assert_eq!(queue.next_batch(0), Some(vec![PageId(2)]));
+        assert_eq!(queue.next_batch(1), Some(vec![PageId(1), PageId(3)]));
+        queue.finish_batch();
+        queue.finish_batch();
+
+        queue.shutdown();
+        assert_eq!(queue.next_batch(0), None);
+        assert_eq!(queue.backpressure(), Backpressure::None);
+    }
+}
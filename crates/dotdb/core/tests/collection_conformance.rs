@@ -0,0 +1,251 @@
+// Conformance suite for the collection manager's storage backends
+//
+// Every case runs against both the in-memory and the persistent backend, which must agree on the
+// whole document API. The documented divergences, durability and dead space, and the in-memory
+// memory budget are checked by the tests after the suite.
+
+use dotdb_core::compaction::strategy::DeadSpaceStrategy;
+use dotdb_core::document::{
+    CollectionManager, DocumentError, DocumentId, ScanOptions, create_in_memory_collection_manager, create_in_memory_collection_manager_with_budget, create_persistent_collection_manager,
+};
+use dotdb_core::indices::{CompositeKey, Index, RangeQuery};
+use dotdb_core::state::db_interface::DbError;
+use dotdb_core::storage_engine::IsolationLevel;
+use serde_json::{Value, json};
+use tempfile::TempDir;
+
+/// A manager under test; a persistent one keeps its directory until the case ends
+struct Backend {
+    manager: CollectionManager,
+    _dir: Option<TempDir>,
+}
+
+fn in_memory() -> Backend {
+    Backend {
+        manager: create_in_memory_collection_manager().unwrap(),
+        _dir: None,
+    }
+}
+
+fn persistent() -> Backend {
+    let dir = TempDir::new().unwrap();
+    Backend {
+        manager: create_persistent_collection_manager(dir.path(), None).unwrap(),
+        _dir: Some(dir),
+    }
+}
+
+/// Runs each case once per backend, in a module named after the backend
+macro_rules! conformance {
+    ($($case:ident),* $(,)?) => {
+        mod in_memory_backend {
+            $(#[test]
+            fn $case() {
+                super::$case(&super::in_memory().manager);
+            })*
+        }
+
+        mod persistent_backend {
+            $(#[test]
+            fn $case() {
+                super::$case(&super::persistent().manager);
+            })*
+        }
+    };
+}
+
+conformance!(
+    crud_round_trip,
+    collections_are_listed_and_dropped,
+    find_matches_field_values,
+    sorted_and_paged_listing,
+    field_and_covering_indexes,
+    namespaces_are_isolated,
+    schemas_reject_invalid_documents,
+    transactions_commit_atomically,
+    history_reads_as_of_earlier_times,
+);
+
+fn crud_round_trip(manager: &CollectionManager) {
+    let id = manager.insert_value("users", json!({"name": "Alice", "age": 30})).unwrap();
+    assert_eq!(manager.get_value("users", &id).unwrap(), Some(json!({"name": "Alice", "age": 30})));
+
+    manager.update_value("users", &id, json!({"name": "Alice", "age": 31})).unwrap();
+    assert_eq!(manager.get_value("users", &id).unwrap().unwrap()["age"], json!(31));
+
+    let fixed = DocumentId::new();
+    assert!(manager.put_value("users", &fixed, json!({"name": "Bob"})).unwrap());
+    assert!(!manager.put_value("users", &fixed, json!({"name": "Bobby"})).unwrap());
+    assert_eq!(manager.count("users").unwrap(), 2);
+
+    assert!(manager.delete("users", &id).unwrap());
+    assert!(!manager.delete("users", &id).unwrap());
+    assert!(!manager.exists("users", &id).unwrap());
+    assert_eq!(manager.get_value("users", &id).unwrap(), None);
+    assert!(manager.update_value("users", &id, json!({})).is_err());
+    assert_eq!(manager.list_document_ids("users").unwrap(), vec![fixed]);
+}
+
+fn collections_are_listed_and_dropped(manager: &CollectionManager) {
+    manager.create_collection("empty").unwrap();
+    manager.insert_value("orders", json!({"total": 5})).unwrap();
+
+    let mut collections = manager.list_collections().unwrap();
+    collections.sort();
+    assert_eq!(collections, vec!["empty", "orders"]);
+
+    assert!(manager.delete_collection("orders").unwrap());
+    assert!(!manager.collection_exists("orders").unwrap());
+    assert_eq!(manager.count("orders").unwrap_or(0), 0);
+    assert!(manager.collection_exists("empty").unwrap());
+}
+
+fn find_matches_field_values(manager: &CollectionManager) {
+    let berlin = manager.insert_value("users", json!({"name": "Ada", "city": "Berlin"})).unwrap();
+    manager.insert_value("users", json!({"name": "Grace", "city": "Paris"})).unwrap();
+    manager.insert_value("users", json!({"name": "Linus"})).unwrap();
+
+    let found = manager.find_by_field("users", "city", &json!("Berlin")).unwrap();
+    assert_eq!(found.iter().map(|(id, _)| id.clone()).collect::<Vec<_>>(), vec![berlin]);
+    assert!(manager.find_by_field("users", "city", &json!("Rome")).unwrap().is_empty());
+}
+
+fn sorted_and_paged_listing(manager: &CollectionManager) {
+    for score in [7, 3, 9, 1, 5] {
+        manager.insert_value("games", json!({"score": score})).unwrap();
+    }
+    let options = ScanOptions {
+        sort_field: Some("score".to_string()),
+        descending: true,
+        limit: Some(2),
+        offset: 1,
+    };
+    let scores: Vec<Value> = manager.list_documents("games", &options).unwrap().into_iter().map(|(_, document)| document["score"].clone()).collect();
+    assert_eq!(scores, vec![json!(7), json!(5)]);
+}
+
+fn field_and_covering_indexes(manager: &CollectionManager) {
+    for i in 0..50 {
+        manager.insert_value("users", json!({"name": format!("user_{i}"), "group": i % 5})).unwrap();
+    }
+
+    let index = manager.build_field_index("users", "group", 16, 0.8).unwrap();
+    assert_eq!(index.len(), 50);
+    let group = serde_json::to_vec(&json!(2)).unwrap();
+    let matches = index.range(&CompositeKey::new(vec![group.clone()]), &CompositeKey::new(vec![group, vec![u8::MAX]])).unwrap();
+    assert_eq!(matches.len(), 10);
+
+    assert!(manager.create_covering_index("users", &["group", "name"]).unwrap());
+    manager.insert_value("users", json!({"name": "late", "group": 2})).unwrap();
+    let projected = manager.find_by_field_projected("users", "group", &json!(2), &["name"]).unwrap();
+    assert_eq!(projected.len(), 11);
+    assert!(projected.iter().any(|document| document.to_value() == json!({"name": "late"})));
+    assert!(manager.drop_covering_index("users", &["group", "name"]).unwrap());
+}
+
+fn namespaces_are_isolated(manager: &CollectionManager) {
+    let tenant = manager.with_namespace("tenant").unwrap();
+    tenant.insert_value("users", json!({"name": "Tenant"})).unwrap();
+    manager.insert_value("users", json!({"name": "Default"})).unwrap();
+
+    assert_eq!(tenant.count("users").unwrap(), 1);
+    assert_eq!(manager.count("users").unwrap(), 1);
+    assert!(manager.list_namespaces().unwrap().contains(&"tenant".to_string()));
+    assert!(manager.drop_namespace("tenant", true).unwrap());
+    assert!(!manager.list_namespaces().unwrap().contains(&"tenant".to_string()));
+}
+
+fn schemas_reject_invalid_documents(manager: &CollectionManager) {
+    let schema = r#"{"type": "object", "required": ["name"], "properties": {"name": {"type": "string"}}}"#;
+    manager.set_schema("users", schema, false).unwrap();
+
+    assert!(matches!(manager.insert_value("users", json!({"name": 5})), Err(DocumentError::SchemaViolation { .. })));
+    manager.insert_value("users", json!({"name": "Valid"})).unwrap();
+    assert_eq!(manager.count("users").unwrap(), 1);
+}
+
+fn transactions_commit_atomically(manager: &CollectionManager) {
+    let mut txn = manager.begin_transaction(IsolationLevel::ReadCommitted);
+    txn.insert_value("accounts", json!({"owner": "a", "balance": 10})).unwrap();
+    txn.insert_value("accounts", json!({"owner": "b", "balance": 20})).unwrap();
+    assert_eq!(manager.count("accounts").unwrap_or(0), 0);
+    txn.commit().unwrap();
+    assert_eq!(manager.count("accounts").unwrap(), 2);
+
+    let mut abandoned = manager.begin_transaction(IsolationLevel::ReadCommitted);
+    abandoned.insert_value("accounts", json!({"owner": "c", "balance": 30})).unwrap();
+    drop(abandoned);
+    assert_eq!(manager.count("accounts").unwrap(), 2);
+}
+
+fn history_reads_as_of_earlier_times(manager: &CollectionManager) {
+    let id = manager.insert_value("users", json!({"name": "First"})).unwrap();
+    let before_update = manager.storage().read_snapshot(u64::MAX).unwrap();
+    manager.update_value("users", &id, json!({"name": "Second"})).unwrap();
+
+    assert_eq!(manager.get_value_as_of("users", &id, before_update).unwrap(), Some(json!({"name": "First"})));
+    assert_eq!(manager.get_value("users", &id).unwrap(), Some(json!({"name": "Second"})));
+}
+
+#[test]
+fn test_only_the_persistent_backend_survives_reopening() {
+    let dir = TempDir::new().unwrap();
+    let id = {
+        let manager = create_persistent_collection_manager(dir.path(), None).unwrap();
+        let id = manager.insert_value("users", json!({"name": "Durable"})).unwrap();
+        manager.flush().unwrap();
+        id
+    };
+    let reopened = create_persistent_collection_manager(dir.path(), None).unwrap();
+    assert_eq!(reopened.get_value("users", &id).unwrap(), Some(json!({"name": "Durable"})));
+
+    // An in-memory manager's documents go with it; flushing has nothing to write
+    let manager = create_in_memory_collection_manager().unwrap();
+    manager.insert_value("users", json!({"name": "Ephemeral"})).unwrap();
+    manager.flush().unwrap();
+    drop(manager);
+    assert!(create_in_memory_collection_manager().unwrap().list_collections().unwrap().is_empty());
+}
+
+#[test]
+fn test_only_the_persistent_backend_reports_dead_space() {
+    for (backend, expect_dead_space) in [(in_memory(), false), (persistent(), true)] {
+        let manager = &backend.manager;
+        let id = manager.insert_value("users", json!({"name": "Old"})).unwrap();
+        manager.update_value("users", &id, json!({"name": "New"})).unwrap();
+
+        let usage = manager.space_usage("users").unwrap();
+        assert_eq!(usage.dead_entries > 0, expect_dead_space);
+        assert_eq!(usage.on_disk_bytes > usage.logical_bytes, expect_dead_space);
+
+        let compaction = manager.compact(&DeadSpaceStrategy::default()).unwrap();
+        if !expect_dead_space {
+            assert_eq!(compaction.segments.bytes_reclaimed, 0);
+        }
+    }
+}
+
+#[test]
+fn test_in_memory_budget_refuses_writes_past_it() {
+    let manager = create_in_memory_collection_manager_with_budget(16 * 1024).unwrap();
+    let payload = "x".repeat(1024);
+
+    let mut stored = Vec::new();
+    let error = loop {
+        match manager.insert_value("blobs", json!({"payload": payload})) {
+            Ok(id) => stored.push(id),
+            Err(error) => break error,
+        }
+        assert!(stored.len() < 64, "the budget was never enforced");
+    };
+    assert!(matches!(error, DocumentError::Database(DbError::MemoryBudgetExceeded { budget: 16384, .. })));
+    assert!(!stored.is_empty());
+
+    // Documents stored before the refusal stay readable, and deleting frees room again
+    assert!(manager.get_value("blobs", &stored[0]).unwrap().is_some());
+    for id in &stored {
+        manager.delete("blobs", id).unwrap();
+    }
+    manager.compact(&DeadSpaceStrategy::default()).unwrap();
+    manager.insert_value("blobs", json!({"payload": payload})).unwrap();
+}
@@ -0,0 +1,362 @@
+// Crash-consistency harness for the storage engine
+//
+// A small document store keeps each document on a fixed run of pages, logs every operation to
+// the WAL as full page images and writes the pages back after the commit is durable. Each round
+// installs a fault injector, runs random operations until an injected torn write or crash stops
+// the storage, optionally loses everything not yet fsynced, then reopens the store and checks that
+// recovery kept every acknowledged commit, applied no operation partially and left every page
+// checksum valid.
+//
+// Set DOTDB_CRASH_SEED and DOTDB_CRASH_ROUNDS to replay a failing seed or run a longer soak.
+
+use dotdb_core::storage_engine::{
+    Failpoint, FailpointAction, FaultInjector, FileFormat, LogEntry, LogSequenceNumber, Page, PageId, PageType, StorageConfig, StorageResult, TriggerAt, VersionId, WalConfig, WriteAheadLog,
+};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::path::Path;
+use tempfile::tempdir;
+
+const SLOTS: u64 = 8;
+const PAGES_PER_DOCUMENT: u64 = 3;
+const PAGE_SIZE: usize = 4096;
+const CHECKPOINT_EVERY: u64 = 5;
+const MAX_OPERATIONS_PER_ROUND: usize = 40;
+/// Page payload prefix: the sequence number of the operation that wrote the page, then the chunk length
+const PAGE_PREFIX: usize = 10;
+
+/// Every document's latest operation sequence number and content, by slot
+type Documents = HashMap<u64, (u64, Option<Value>)>;
+
+/// One atomic operation: the new content of one or two documents, `None` deleting
+#[derive(Debug, Clone)]
+struct Operation {
+    seq: u64,
+    writes: Vec<(u64, Option<Value>)>,
+}
+
+impl Operation {
+    fn random(rng: &mut StdRng, seq: u64) -> Self {
+        let mut slots: Vec<u64> = (0..SLOTS).collect();
+        slots.shuffle(rng);
+        let writes = slots
+            .into_iter()
+            .take(rng.gen_range(1..=2))
+            .map(|slot| {
+                // Large enough bodies span all of a document's pages
+                let content = rng.gen_bool(0.85).then(|| json!({"slot": slot, "seq": seq, "body": "x".repeat(rng.gen_range(0..9000))}));
+                (slot, content)
+            })
+            .collect();
+        Self { seq, writes }
+    }
+
+    /// Page images of every document the operation writes
+    fn pages(&self) -> Vec<Page> {
+        let capacity = Page::new(PageId(0), PageType::Data, VersionId(0), PAGE_SIZE).data.len() - PAGE_PREFIX;
+        let mut pages = Vec::new();
+        for (slot, content) in &self.writes {
+            let bytes = content.as_ref().map(|content| serde_json::to_vec(content).unwrap()).unwrap_or_default();
+            let mut chunks = bytes.chunks(capacity);
+            for index in 0..PAGES_PER_DOCUMENT {
+                let chunk = chunks.next().unwrap_or_default();
+                let mut page = Page::new(page_id(*slot, index), PageType::Data, VersionId(self.seq), PAGE_SIZE);
+                page.data[..8].copy_from_slice(&self.seq.to_le_bytes());
+                page.data[8..PAGE_PREFIX].copy_from_slice(&(chunk.len() as u16).to_le_bytes());
+                page.data[PAGE_PREFIX..PAGE_PREFIX + chunk.len()].copy_from_slice(chunk);
+                pages.push(page);
+            }
+        }
+        pages
+    }
+
+    fn apply(&self, documents: &mut Documents) {
+        for (slot, content) in &self.writes {
+            documents.insert(*slot, (self.seq, content.clone()));
+        }
+    }
+}
+
+fn page_id(slot: u64, index: u64) -> PageId {
+    PageId(1 + slot * PAGES_PER_DOCUMENT + index)
+}
+
+/// What reopening the store found and repaired
+#[derive(Debug)]
+struct Recovery {
+    /// Pages whose checksum failed before the WAL was replayed
+    torn_pages: usize,
+    transactions_replayed: usize,
+}
+
+/// Documents on pages, made durable through the WAL
+struct PageStore {
+    wal: WriteAheadLog,
+    data: FileFormat,
+    next_txn: u64,
+    commits_since_checkpoint: u64,
+}
+
+impl PageStore {
+    /// Open the store, formatting it if new, and replay committed operations logged since the last checkpoint
+    fn open(dir: &Path) -> StorageResult<(Self, Recovery)> {
+        let mut data = FileFormat::new(StorageConfig {
+            path: dir.join("data.db"),
+            page_size: PAGE_SIZE,
+            ..StorageConfig::default()
+        });
+        data.init()?;
+        if data.total_pages() <= 1 {
+            for slot in 0..SLOTS {
+                for mut page in (Operation { seq: 0, writes: vec![(slot, None)] }).pages() {
+                    data.write_page(&mut page)?;
+                }
+            }
+            data.sync()?;
+        }
+
+        let wal = WriteAheadLog::new(WalConfig {
+            directory: dir.join("wal"),
+            archive: None,
+            group_commit: None,
+            ..WalConfig::default()
+        })?;
+
+        let torn_pages = data.verify()?.corrupt_pages.len();
+        let mut max_txn = 0;
+        wal.read_records(|entry| {
+            max_txn = max_txn.max(entry.transaction_id());
+            Ok(())
+        })?;
+        // Committed page images are rewritten whole, which also repairs torn pages
+        let report = wal.recover_to_lsn(LogSequenceNumber { file_id: u32::MAX, offset: u64::MAX }, |entry| match entry.page() {
+            Some(mut page) => data.write_page(&mut page),
+            None => Ok(()),
+        })?;
+
+        let mut store = Self {
+            wal,
+            data,
+            next_txn: max_txn + 1,
+            commits_since_checkpoint: 0,
+        };
+        store.checkpoint()?;
+        Ok((
+            store,
+            Recovery {
+                torn_pages,
+                transactions_replayed: report.transactions_applied,
+            },
+        ))
+    }
+
+    /// Log the operation's pages and make the commit durable; `Ok` acknowledges the operation
+    fn commit(&mut self, operation: &Operation) -> StorageResult<()> {
+        let txn = self.next_txn;
+        self.next_txn += 1;
+        self.wal.append(&LogEntry::begin_transaction(LogSequenceNumber::default(), txn))?;
+        for page in operation.pages() {
+            self.wal.append(&LogEntry::write_page(LogSequenceNumber::default(), txn, &page))?;
+        }
+        self.wal.append_durable(&LogEntry::commit_transaction(LogSequenceNumber::default(), txn))?;
+        Ok(())
+    }
+
+    /// Write an acknowledged operation's pages back to the data file, checkpointing now and then
+    fn write_back(&mut self, operation: &Operation) -> StorageResult<()> {
+        for mut page in operation.pages() {
+            self.data.write_page(&mut page)?;
+        }
+        self.commits_since_checkpoint += 1;
+        if self.commits_since_checkpoint >= CHECKPOINT_EVERY {
+            self.checkpoint()?;
+        }
+        Ok(())
+    }
+
+    /// Sync the data file so the WAL before this point is no longer needed for recovery
+    fn checkpoint(&mut self) -> StorageResult<()> {
+        self.data.sync()?;
+        self.wal.append_durable(&LogEntry::checkpoint(LogSequenceNumber::default(), VersionId(self.next_txn)))?;
+        self.commits_since_checkpoint = 0;
+        Ok(())
+    }
+
+    /// Read every document, failing if a page checksum fails or a document's pages were
+    /// written by different operations
+    fn documents(&mut self) -> Result<Documents, String> {
+        let report = self.data.verify().map_err(|e| e.to_string())?;
+        if !report.is_clean() {
+            return Err(format!("{} pages fail their checksum after recovery", report.corrupt_pages.len()));
+        }
+
+        let mut documents = Documents::new();
+        for slot in 0..SLOTS {
+            let mut seqs = Vec::new();
+            let mut bytes = Vec::new();
+            for index in 0..PAGES_PER_DOCUMENT {
+                let page = self.data.read_page(page_id(slot, index)).map_err(|e| e.to_string())?;
+                seqs.push(u64::from_le_bytes(page.data[..8].try_into().unwrap()));
+                let len = u16::from_le_bytes(page.data[8..PAGE_PREFIX].try_into().unwrap()) as usize;
+                bytes.extend_from_slice(&page.data[PAGE_PREFIX..PAGE_PREFIX + len]);
+            }
+            if seqs.iter().any(|seq| *seq != seqs[0]) {
+                return Err(format!("document {slot} is partially written: its pages come from operations {seqs:?}"));
+            }
+            let content = (!bytes.is_empty()).then(|| serde_json::from_slice(&bytes)).transpose().map_err(|e| format!("document {slot}: {e}"))?;
+            documents.insert(slot, (seqs[0], content));
+        }
+        Ok(documents)
+    }
+}
+
+fn empty_documents() -> Documents {
+    (0..SLOTS).map(|slot| (slot, (0, None))).collect()
+}
+
+/// Check the recovered documents against the acknowledged ones. The operation in flight at
+/// the crash may have become durable without being acknowledged, but only as a whole; returns
+/// whether it did.
+fn check_recovered(recovered: &Documents, acknowledged: &Documents, in_flight: Option<&Operation>) -> Result<bool, String> {
+    let landed = in_flight.filter(|operation| operation.writes.iter().all(|(slot, content)| recovered[slot] == (operation.seq, content.clone())));
+    let mut expected = acknowledged.clone();
+    if let Some(operation) = landed {
+        operation.apply(&mut expected);
+    }
+
+    for slot in 0..SLOTS {
+        let (seq, _) = &recovered[&slot];
+        let (expected_seq, _) = &expected[&slot];
+        if recovered[&slot] != expected[&slot] {
+            return Err(format!("document {slot} holds operation {seq} after recovery, expected operation {expected_seq}"));
+        }
+    }
+    Ok(landed.is_some())
+}
+
+fn env_or(name: &str, default: u64) -> u64 {
+    std::env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+}
+
+#[test]
+fn test_recovery_after_random_crashes() {
+    let seed = env_or("DOTDB_CRASH_SEED", 0x5eed_d07d);
+    let rounds = env_or("DOTDB_CRASH_ROUNDS", 60);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let dir = tempdir().unwrap();
+    drop(PageStore::open(dir.path()).unwrap());
+
+    let mut acknowledged = empty_documents();
+    let mut seq = 0;
+    let mut crashes = 0;
+
+    for round in 0..rounds {
+        let failpoint = *Failpoint::ALL.choose(&mut rng).unwrap();
+        let action = if !failpoint.is_sync() && rng.gen_bool(0.6) {
+            FailpointAction::TornWrite {
+                persisted: rng.gen_range(0..PAGE_SIZE),
+            }
+        } else {
+            FailpointAction::Crash
+        };
+        let hit = rng.gen_range(1..=30);
+        let context = format!("seed {seed:#x}, round {round}: {action:?} at {failpoint} hit {hit}");
+
+        let injector = FaultInjector::install(dir.path(), TriggerAt::new(failpoint, hit, action));
+        let mut in_flight = None;
+        // Recovery itself may crash
+        if let Ok((mut store, _)) = PageStore::open(dir.path()) {
+            for _ in 0..MAX_OPERATIONS_PER_ROUND {
+                seq += 1;
+                let operation = Operation::random(&mut rng, seq);
+                if store.commit(&operation).is_err() {
+                    in_flight = Some(operation);
+                    break;
+                }
+                operation.apply(&mut acknowledged);
+                if store.write_back(&operation).is_err() {
+                    break;
+                }
+            }
+        }
+        if injector.crashed().is_some() {
+            crashes += 1;
+        }
+        if rng.gen_bool(0.5) {
+            injector.power_loss().unwrap();
+        }
+        drop(injector);
+
+        let (mut store, _) = PageStore::open(dir.path()).unwrap_or_else(|e| panic!("{context}: recovery failed: {e}"));
+        let recovered = store.documents().unwrap_or_else(|e| panic!("{context}: {e}"));
+        if check_recovered(&recovered, &acknowledged, in_flight.as_ref()).unwrap_or_else(|e| panic!("{context}: {e}")) {
+            in_flight.unwrap().apply(&mut acknowledged);
+        }
+    }
+
+    assert!(crashes > rounds / 2, "only {crashes} of {rounds} rounds crashed");
+}
+
+#[test]
+fn test_torn_page_is_rewritten_from_the_wal() {
+    let dir = tempdir().unwrap();
+    let (mut store, _) = PageStore::open(dir.path()).unwrap();
+    let first = Operation {
+        seq: 1,
+        writes: vec![(3, Some(json!({"body": "a".repeat(9000)})))],
+    };
+    store.commit(&first).unwrap();
+    store.write_back(&first).unwrap();
+    drop(store);
+
+    // The second page of the next write-back is torn halfway
+    let (mut store, _) = PageStore::open(dir.path()).unwrap();
+    let injector = FaultInjector::install(dir.path(), TriggerAt::new(Failpoint::PageWrite, 2, FailpointAction::TornWrite { persisted: PAGE_SIZE / 2 }));
+    let second = Operation {
+        seq: 2,
+        writes: vec![(3, Some(json!({"body": "b".repeat(9000)})))],
+    };
+    store.commit(&second).unwrap();
+    assert!(store.write_back(&second).is_err());
+    assert_eq!(injector.crashed(), Some(Failpoint::PageWrite));
+    drop(store);
+    drop(injector);
+
+    let (mut store, recovery) = PageStore::open(dir.path()).unwrap();
+    assert_eq!(recovery.torn_pages, 1);
+    assert_eq!(recovery.transactions_replayed, 1);
+    assert_eq!(store.documents().unwrap()[&3], (2, second.writes[0].1.clone()));
+}
+
+#[test]
+fn test_dropped_fsyncs_lose_acknowledged_commits() {
+    let dir = tempdir().unwrap();
+    drop(PageStore::open(dir.path()).unwrap());
+
+    // A disk that acknowledges WAL fsyncs without persisting anything breaks durability, and the checks notice
+    let injector = FaultInjector::install(
+        dir.path(),
+        |failpoint: Failpoint, _: &Path, _: usize| {
+            if failpoint == Failpoint::WalSync { FailpointAction::DropSync } else { FailpointAction::Continue }
+        },
+    );
+    let (mut store, _) = PageStore::open(dir.path()).unwrap();
+    let operation = Operation {
+        seq: 1,
+        writes: vec![(0, Some(json!({"body": "lost"})))],
+    };
+    store.commit(&operation).unwrap();
+    let mut acknowledged = empty_documents();
+    operation.apply(&mut acknowledged);
+    drop(store);
+    assert!(injector.hits(Failpoint::WalSync) > 0);
+    injector.power_loss().unwrap();
+    drop(injector);
+
+    let (mut store, _) = PageStore::open(dir.path()).unwrap();
+    let error = check_recovered(&store.documents().unwrap(), &acknowledged, None).unwrap_err();
+    assert!(error.contains("document 0 holds operation 0"), "{error}");
+}
@@ -0,0 +1,93 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Round trips of an execution making many small state writes
+//!
+//! One execution writes 500 small values and reads some of them back. Compares
+//! sending every write on its own, as the gateway used to, against batching them
+//! through a write pipeline. The backend adds a fixed delay to each call to stand
+//! in for the gateway-to-runtime round trip.
+
+use async_trait::async_trait;
+use dotlanth_api::db::pipeline::{BatchError, MemoryStateBackend, PipelineConfig, StateBackend, StateWrite, WritePipeline};
+use dotlanth_api::error::ApiResult;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const WRITES: usize = 500;
+const VALUE_BYTES: usize = 32;
+/// Every this many writes, the execution reads back a value it wrote
+const READ_EVERY: usize = 10;
+const ROUND_TRIP: Duration = Duration::from_micros(250);
+
+/// In-memory state behind a simulated network round trip
+struct RemoteState {
+    state: MemoryStateBackend,
+}
+
+#[async_trait]
+impl StateBackend for RemoteState {
+    async fn get(&self, collection: &str, key: &str) -> ApiResult<Option<Vec<u8>>> {
+        tokio::time::sleep(ROUND_TRIP).await;
+        self.state.get(collection, key).await
+    }
+
+    async fn apply_batch(&self, request_id: &str, writes: &[StateWrite]) -> Result<(), BatchError> {
+        tokio::time::sleep(ROUND_TRIP).await;
+        self.state.apply_batch(request_id, writes).await
+    }
+}
+
+/// Runs one execution, returning its duration and the round trips it made
+async fn execution(config: PipelineConfig) -> (Duration, u64) {
+    let backend = Arc::new(RemoteState { state: MemoryStateBackend::new() });
+    let started = Instant::now();
+
+    let mut writes = WritePipeline::new(backend.clone(), "bench", config);
+    for i in 0..WRITES {
+        writes.put("counters", &format!("key-{}", i % 200), vec![i as u8; VALUE_BYTES]).await.unwrap();
+        if i % READ_EVERY == READ_EVERY - 1 {
+            let value = writes.get("counters", &format!("key-{}", i % 200)).await.unwrap();
+            assert_eq!(value.as_deref(), Some(&[i as u8; VALUE_BYTES][..]));
+        }
+    }
+    writes.commit().await.unwrap();
+
+    (started.elapsed(), backend.state.round_trips())
+}
+
+fn report(name: &str, (elapsed, round_trips): (Duration, u64)) -> (Duration, u64) {
+    println!("{name:<16} {round_trips:>5} round trips  {elapsed:>10.3?}");
+    (elapsed, round_trips)
+}
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+    println!("state_write_pipeline: {WRITES} writes of {VALUE_BYTES} bytes, a read every {READ_EVERY} writes, {ROUND_TRIP:?} per round trip");
+
+    let unbatched = PipelineConfig {
+        max_writes: 1,
+        ..PipelineConfig::default()
+    };
+    let (before, before_trips) = report("unbatched", runtime.block_on(execution(unbatched)));
+    let (after, after_trips) = report("batched", runtime.block_on(execution(PipelineConfig::default())));
+
+    println!(
+        "round trips: {:.1}x fewer, execution: {:.1}x faster",
+        before_trips as f64 / after_trips.max(1) as f64,
+        before.as_secs_f64() / after.as_secs_f64().max(f64::EPSILON)
+    );
+}
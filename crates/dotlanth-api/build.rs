@@ -25,7 +25,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         // Compile proto files if they exist
         tonic_build::configure()
             .build_server(false) // We only need client
-            .compile(&[format!("{}/vm_service.proto", proto_dir), format!("{}/common.proto", proto_dir)], &[proto_dir])
+            .compile(
+                &[
+                    format!("{}/vm_service.proto", proto_dir),
+                    format!("{}/database_service.proto", proto_dir),
+                    format!("{}/common.proto", proto_dir),
+                ],
+                &[proto_dir],
+            )
             .unwrap_or_else(|e| {
                 println!("cargo:warning=Failed to compile proto files: {}", e);
             });
@@ -0,0 +1,623 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Admission control for dot executions
+//!
+//! Executions are tracked per dot and across the gateway. A request starts right
+//! away while both limits have room; once the gateway limit is reached it waits in
+//! a small bounded queue, unless the time it would wait already exceeds its
+//! deadline. A dot whose running and queued executions reach the per-dot limit has
+//! further requests rejected without queueing, so one busy dot cannot fill the
+//! queue shared by all of them. Rejected requests get a 429 with `Retry-After`.
+
+use crate::error::ApiError;
+use crate::grpc_pool::parse_env;
+use hyper::HeaderMap;
+use metrics::counter;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+use tracing::debug;
+use utoipa::ToSchema;
+
+/// Request header carrying how long, in milliseconds, the client waits for an execution
+pub const TIMEOUT_HEADER: &str = "x-dotlanth-timeout-ms";
+
+/// Weight of the latest execution in the moving average of execution time
+const AVERAGE_WEIGHT: f64 = 0.2;
+
+/// Admission control settings, adjustable at runtime through the gateway config endpoint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AdmissionConfig {
+    pub enabled: bool,
+    /// Executions running at once across all dots
+    pub max_in_flight: usize,
+    /// Executions running or queued at once for a single dot
+    pub max_in_flight_per_dot: usize,
+    /// Requests waiting for a free slot before further ones are rejected; 0 disables queueing
+    pub max_queued: usize,
+    /// Deadline of requests that do not send `x-dotlanth-timeout-ms`
+    pub default_timeout_ms: u64,
+}
+
+impl Default for AdmissionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_in_flight: 64,
+            max_in_flight_per_dot: 16,
+            max_queued: 32,
+            default_timeout_ms: 30_000,
+        }
+    }
+}
+
+impl AdmissionConfig {
+    /// Load admission settings from `DOTLANTH_ADMISSION_*` variables
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Some(enabled) = parse_env("DOTLANTH_ADMISSION_ENABLED") {
+            config.enabled = enabled;
+        }
+        if let Some(max_in_flight) = parse_env::<usize>("DOTLANTH_ADMISSION_MAX_IN_FLIGHT").filter(|&max| max > 0) {
+            config.max_in_flight = max_in_flight;
+        }
+        if let Some(max_in_flight_per_dot) = parse_env::<usize>("DOTLANTH_ADMISSION_MAX_IN_FLIGHT_PER_DOT").filter(|&max| max > 0) {
+            config.max_in_flight_per_dot = max_in_flight_per_dot;
+        }
+        if let Some(max_queued) = parse_env("DOTLANTH_ADMISSION_MAX_QUEUED") {
+            config.max_queued = max_queued;
+        }
+        if let Some(default_timeout_ms) = parse_env::<u64>("DOTLANTH_ADMISSION_DEFAULT_TIMEOUT_MS").filter(|&ms| ms > 0) {
+            config.default_timeout_ms = default_timeout_ms;
+        }
+
+        config
+    }
+}
+
+/// Admission settings to change; fields left out keep their current value
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AdmissionConfigUpdate {
+    pub enabled: Option<bool>,
+    pub max_in_flight: Option<usize>,
+    pub max_in_flight_per_dot: Option<usize>,
+    pub max_queued: Option<usize>,
+    pub default_timeout_ms: Option<u64>,
+}
+
+impl AdmissionConfigUpdate {
+    /// `config` with this update applied
+    pub fn apply(&self, config: &AdmissionConfig) -> Result<AdmissionConfig, ApiError> {
+        let positive = |name: &str, value: Option<u64>| match value {
+            Some(0) => Err(ApiError::BadRequest {
+                message: format!("{} must be greater than zero", name),
+            }),
+            _ => Ok(()),
+        };
+        positive("max_in_flight", self.max_in_flight.map(|max| max as u64))?;
+        positive("max_in_flight_per_dot", self.max_in_flight_per_dot.map(|max| max as u64))?;
+        positive("default_timeout_ms", self.default_timeout_ms)?;
+
+        Ok(AdmissionConfig {
+            enabled: self.enabled.unwrap_or(config.enabled),
+            max_in_flight: self.max_in_flight.unwrap_or(config.max_in_flight),
+            max_in_flight_per_dot: self.max_in_flight_per_dot.unwrap_or(config.max_in_flight_per_dot),
+            max_queued: self.max_queued.unwrap_or(config.max_queued),
+            default_timeout_ms: self.default_timeout_ms.unwrap_or(config.default_timeout_ms),
+        })
+    }
+}
+
+/// Why a request was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShedReason {
+    /// The dot had as many executions running or queued as it may, or the gateway
+    /// was at its limit with queueing disabled
+    ConcurrencyLimit,
+    /// The gateway was at its limit and the queue was full
+    QueueFull,
+    /// The request could not have started before its deadline
+    DeadlineUnreachable,
+}
+
+impl ShedReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ShedReason::ConcurrencyLimit => "concurrency_limit",
+            ShedReason::QueueFull => "queue_full",
+            ShedReason::DeadlineUnreachable => "deadline_unreachable",
+        }
+    }
+}
+
+/// A rejected request
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shed {
+    pub reason: ShedReason,
+    /// When the caller may expect a slot to be free
+    pub retry_after: Duration,
+}
+
+impl Shed {
+    /// `Retry-After` value, rounded up to whole seconds
+    pub fn retry_after_secs(&self) -> u64 {
+        self.retry_after.as_secs_f64().ceil().max(1.0) as u64
+    }
+
+    /// Error returned to rejected callers
+    pub fn to_error(&self, dot_id: &str) -> ApiError {
+        let message = match self.reason {
+            ShedReason::ConcurrencyLimit => format!("Too many executions in flight for dot '{}'", dot_id),
+            ShedReason::QueueFull => "Execution queue is full".to_string(),
+            ShedReason::DeadlineUnreachable => format!("Execution of dot '{}' could not start before its deadline", dot_id),
+        };
+        ApiError::Overloaded {
+            message,
+            reason: self.reason.as_str(),
+            retry_after_secs: self.retry_after_secs(),
+        }
+    }
+}
+
+/// Requests rejected since startup, by reason
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, ToSchema)]
+pub struct ShedCounters {
+    pub concurrency_limit: u64,
+    pub queue_full: u64,
+    pub deadline_unreachable: u64,
+}
+
+/// Admission state and counters
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct AdmissionCounters {
+    /// Executions currently running
+    pub running: usize,
+    /// Requests currently waiting for a slot
+    pub queued: usize,
+    /// Requests admitted since startup, whether right away or after queueing
+    pub admitted: u64,
+    /// Requests that waited in the queue since startup
+    pub queued_total: u64,
+    pub shed: ShedCounters,
+    /// Moving average of execution time, once an execution finished
+    pub average_execution_ms: Option<f64>,
+}
+
+/// A request waiting for a slot
+struct Waiter {
+    id: u64,
+    dot_id: String,
+    grant: oneshot::Sender<()>,
+}
+
+#[derive(Default)]
+struct AdmissionState {
+    running: usize,
+    /// Running and queued executions by dot
+    per_dot: HashMap<String, usize>,
+    queue: VecDeque<Waiter>,
+    next_waiter: u64,
+    average_execution: Option<Duration>,
+}
+
+impl AdmissionState {
+    /// Expected time until the request at `position` in the queue (1 for the head) gets
+    /// a slot, assuming slots free up at the average execution rate
+    fn expected_wait(&self, position: usize, max_in_flight: usize) -> Duration {
+        let Some(average) = self.average_execution else {
+            return Duration::ZERO;
+        };
+        let completions = self.running.saturating_sub(max_in_flight) + position;
+        average.mul_f64(completions as f64 / max_in_flight.max(1) as f64)
+    }
+
+    fn leave_dot(&mut self, dot_id: &str) {
+        if let Some(count) = self.per_dot.get_mut(dot_id) {
+            *count -= 1;
+            if *count == 0 {
+                self.per_dot.remove(dot_id);
+            }
+        }
+    }
+}
+
+/// Tracks executions in flight and decides which requests may start
+pub struct AdmissionController {
+    config: RwLock<AdmissionConfig>,
+    state: Mutex<AdmissionState>,
+    admitted: AtomicU64,
+    queued_total: AtomicU64,
+    shed_concurrency_limit: AtomicU64,
+    shed_queue_full: AtomicU64,
+    shed_deadline_unreachable: AtomicU64,
+}
+
+impl AdmissionController {
+    pub fn new(config: AdmissionConfig) -> Self {
+        Self {
+            config: RwLock::new(config),
+            state: Mutex::new(AdmissionState::default()),
+            admitted: AtomicU64::new(0),
+            queued_total: AtomicU64::new(0),
+            shed_concurrency_limit: AtomicU64::new(0),
+            shed_queue_full: AtomicU64::new(0),
+            shed_deadline_unreachable: AtomicU64::new(0),
+        }
+    }
+
+    pub fn config(&self) -> AdmissionConfig {
+        self.config.read().clone()
+    }
+
+    /// Replace the limits. Executions already running keep their slot; queued requests
+    /// start at once if the new limits leave room for them.
+    pub fn set_config(&self, config: AdmissionConfig) {
+        *self.config.write() = config;
+        let mut state = self.state.lock();
+        self.grant_waiters(&mut state);
+    }
+
+    /// How long the client of a request waits for it, from `x-dotlanth-timeout-ms` or the default
+    pub fn timeout(&self, headers: &HeaderMap) -> Result<Duration, ApiError> {
+        match headers.get(TIMEOUT_HEADER) {
+            Some(value) => value
+                .to_str()
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .filter(|&ms| ms > 0)
+                .map(Duration::from_millis)
+                .ok_or_else(|| ApiError::BadRequest {
+                    message: format!("Invalid {} header: expected a positive number of milliseconds", TIMEOUT_HEADER),
+                }),
+            None => Ok(Duration::from_millis(self.config.read().default_timeout_ms)),
+        }
+    }
+
+    /// Wait for a slot to execute `dot_id`, giving up once `timeout` has passed
+    ///
+    /// The returned permit holds the slot until it is dropped.
+    pub async fn admit(self: &Arc<Self>, dot_id: &str, timeout: Duration) -> Result<AdmissionPermit, Shed> {
+        let deadline = Instant::now() + timeout;
+        let (id, granted) = {
+            let config = self.config.read().clone();
+            if !config.enabled {
+                return Ok(AdmissionPermit::untracked());
+            }
+
+            let mut state = self.state.lock();
+            let dot_load = state.per_dot.get(dot_id).copied().unwrap_or(0);
+            if dot_load >= config.max_in_flight_per_dot {
+                let retry_after = state.average_execution.unwrap_or_default();
+                return Err(self.shed(ShedReason::ConcurrencyLimit, retry_after));
+            }
+
+            if state.running < config.max_in_flight && state.queue.is_empty() {
+                state.running += 1;
+                *state.per_dot.entry(dot_id.to_string()).or_default() += 1;
+                self.admitted.fetch_add(1, Ordering::Relaxed);
+                return Ok(self.permit(dot_id));
+            }
+
+            let position = state.queue.len() + 1;
+            let wait = state.expected_wait(position, config.max_in_flight);
+            if config.max_queued == 0 {
+                return Err(self.shed(ShedReason::ConcurrencyLimit, wait));
+            }
+            if state.queue.len() >= config.max_queued {
+                return Err(self.shed(ShedReason::QueueFull, wait));
+            }
+            if wait >= timeout {
+                return Err(self.shed(ShedReason::DeadlineUnreachable, wait));
+            }
+
+            let id = state.next_waiter;
+            state.next_waiter += 1;
+            let (grant, granted) = oneshot::channel();
+            state.queue.push_back(Waiter {
+                id,
+                dot_id: dot_id.to_string(),
+                grant,
+            });
+            *state.per_dot.entry(dot_id.to_string()).or_default() += 1;
+            self.queued_total.fetch_add(1, Ordering::Relaxed);
+            (id, granted)
+        };
+
+        // Withdraws the request if the caller goes away while it is queued
+        let mut queued = QueuedRequest {
+            controller: self.clone(),
+            id,
+            dot_id: dot_id.to_string(),
+            done: false,
+        };
+        if tokio::time::timeout_at(deadline, granted).await.is_err() && queued.withdraw() {
+            let retry_after = {
+                let state = self.state.lock();
+                state.expected_wait(state.queue.len() + 1, self.config.read().max_in_flight)
+            };
+            return Err(self.shed(ShedReason::DeadlineUnreachable, retry_after));
+        }
+
+        // A slot was handed over, either before the deadline or while it passed
+        queued.done = true;
+        Ok(self.permit(dot_id))
+    }
+
+    pub fn counters(&self) -> AdmissionCounters {
+        let state = self.state.lock();
+        AdmissionCounters {
+            running: state.running,
+            queued: state.queue.len(),
+            admitted: self.admitted.load(Ordering::Relaxed),
+            queued_total: self.queued_total.load(Ordering::Relaxed),
+            shed: ShedCounters {
+                concurrency_limit: self.shed_concurrency_limit.load(Ordering::Relaxed),
+                queue_full: self.shed_queue_full.load(Ordering::Relaxed),
+                deadline_unreachable: self.shed_deadline_unreachable.load(Ordering::Relaxed),
+            },
+            average_execution_ms: state.average_execution.map(|average| average.as_secs_f64() * 1000.0),
+        }
+    }
+
+    fn permit(self: &Arc<Self>, dot_id: &str) -> AdmissionPermit {
+        AdmissionPermit {
+            slot: Some((self.clone(), dot_id.to_string())),
+            started: Instant::now(),
+        }
+    }
+
+    fn shed(&self, reason: ShedReason, retry_after: Duration) -> Shed {
+        let counter = match reason {
+            ShedReason::ConcurrencyLimit => &self.shed_concurrency_limit,
+            ShedReason::QueueFull => &self.shed_queue_full,
+            ShedReason::DeadlineUnreachable => &self.shed_deadline_unreachable,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        counter!("execution_admission_shed_total", 1, "reason" => reason.as_str());
+        debug!("Shedding execution request: {}", reason.as_str());
+        Shed { reason, retry_after }
+    }
+
+    /// Free the slot of a finished execution and hand it to the next queued request
+    fn release(&self, dot_id: &str, elapsed: Option<Duration>) {
+        let mut state = self.state.lock();
+        if let Some(elapsed) = elapsed {
+            state.average_execution = Some(match state.average_execution {
+                Some(average) => average.mul_f64(1.0 - AVERAGE_WEIGHT) + elapsed.mul_f64(AVERAGE_WEIGHT),
+                None => elapsed,
+            });
+        }
+        state.running = state.running.saturating_sub(1);
+        state.leave_dot(dot_id);
+        self.grant_waiters(&mut state);
+    }
+
+    /// Start queued requests while there is room for them
+    fn grant_waiters(&self, state: &mut AdmissionState) {
+        let config = self.config.read().clone();
+        while !config.enabled || state.running < config.max_in_flight {
+            let Some(waiter) = state.queue.pop_front() else {
+                break;
+            };
+            if waiter.grant.send(()).is_ok() {
+                state.running += 1;
+                self.admitted.fetch_add(1, Ordering::Relaxed);
+            } else {
+                state.leave_dot(&waiter.dot_id);
+            }
+        }
+    }
+}
+
+/// A queued request, withdrawn from the queue if dropped before it got a slot
+struct QueuedRequest {
+    controller: Arc<AdmissionController>,
+    id: u64,
+    dot_id: String,
+    done: bool,
+}
+
+impl QueuedRequest {
+    /// Remove the request from the queue; false if it was already given a slot
+    fn withdraw(&mut self) -> bool {
+        let mut state = self.controller.state.lock();
+        let Some(position) = state.queue.iter().position(|waiter| waiter.id == self.id) else {
+            return false;
+        };
+        state.queue.remove(position);
+        state.leave_dot(&self.dot_id);
+        self.done = true;
+        true
+    }
+}
+
+impl Drop for QueuedRequest {
+    fn drop(&mut self) {
+        if !self.done && !self.withdraw() {
+            // The slot was handed over after the caller stopped waiting for it
+            self.controller.release(&self.dot_id, None);
+        }
+    }
+}
+
+/// A slot for one execution, freed when dropped
+pub struct AdmissionPermit {
+    slot: Option<(Arc<AdmissionController>, String)>,
+    started: Instant,
+}
+
+impl AdmissionPermit {
+    /// A permit not counted against any limit, given out while admission control is disabled
+    fn untracked() -> Self {
+        Self { slot: None, started: Instant::now() }
+    }
+}
+
+impl Drop for AdmissionPermit {
+    fn drop(&mut self) {
+        if let Some((controller, dot_id)) = self.slot.take() {
+            controller.release(&dot_id, Some(self.started.elapsed()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controller(max_in_flight: usize, max_in_flight_per_dot: usize, max_queued: usize) -> Arc<AdmissionController> {
+        Arc::new(AdmissionController::new(AdmissionConfig {
+            enabled: true,
+            max_in_flight,
+            max_in_flight_per_dot,
+            max_queued,
+            default_timeout_ms: 1000,
+        }))
+    }
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[tokio::test]
+    async fn test_limits_and_queue() {
+        let admission = controller(2, 2, 1);
+        let first = admission.admit("a", TIMEOUT).await.unwrap();
+        let _second = admission.admit("b", TIMEOUT).await.unwrap();
+
+        // A third dot-a request would exceed the gateway limit and waits in the queue
+        let queued = tokio::spawn({
+            let admission = admission.clone();
+            async move { admission.admit("a", TIMEOUT).await.map(|_| ()) }
+        });
+        while admission.counters().queued == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        // Dot a now has two executions running or queued, and the queue is full
+        assert_eq!(admission.admit("a", TIMEOUT).await.err().unwrap().reason, ShedReason::ConcurrencyLimit);
+        let full = admission.admit("c", TIMEOUT).await.err().unwrap();
+        assert_eq!(full.reason, ShedReason::QueueFull);
+        assert_eq!(full.retry_after_secs(), 1);
+
+        drop(first);
+        queued.await.unwrap().unwrap();
+
+        let counters = admission.counters();
+        assert_eq!((counters.running, counters.queued, counters.admitted, counters.queued_total), (1, 0, 3, 1));
+        assert_eq!(
+            counters.shed,
+            ShedCounters {
+                concurrency_limit: 1,
+                queue_full: 1,
+                deadline_unreachable: 0,
+            }
+        );
+        assert!(counters.average_execution_ms.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_deadline_shedding() {
+        let admission = controller(1, 4, 4);
+        admission.state.lock().average_execution = Some(Duration::from_millis(200));
+        let _running = admission.admit("a", TIMEOUT).await.unwrap();
+
+        // The head of the queue is expected to wait one average execution
+        let unreachable = admission.admit("a", Duration::from_millis(100)).await.err().unwrap();
+        assert_eq!(unreachable.reason, ShedReason::DeadlineUnreachable);
+        assert_eq!(admission.counters().queued, 0);
+
+        // A request whose deadline passes while it is queued is withdrawn
+        admission.state.lock().average_execution = Some(Duration::from_millis(1));
+        let expired = admission.admit("a", Duration::from_millis(20)).await.err().unwrap();
+        assert_eq!(expired.reason, ShedReason::DeadlineUnreachable);
+
+        let counters = admission.counters();
+        assert_eq!((counters.queued, counters.queued_total, counters.shed.deadline_unreachable), (0, 1, 2));
+        assert!(admission.state.lock().per_dot.get("a") == Some(&1));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_and_reconfigured() {
+        let admission = controller(1, 4, 4);
+        let running = admission.admit("a", TIMEOUT).await.unwrap();
+
+        // A caller that goes away leaves the queue
+        let cancelled = tokio::spawn({
+            let admission = admission.clone();
+            async move { admission.admit("a", TIMEOUT).await.map(|_| ()) }
+        });
+        while admission.counters().queued == 0 {
+            tokio::task::yield_now().await;
+        }
+        cancelled.abort();
+        let _ = cancelled.await;
+        assert_eq!(admission.counters().queued, 0);
+
+        // Raising the gateway limit starts queued requests at once
+        let waiting = tokio::spawn({
+            let admission = admission.clone();
+            async move { admission.admit("b", TIMEOUT).await.map(|permit| drop(permit)) }
+        });
+        while admission.counters().queued == 0 {
+            tokio::task::yield_now().await;
+        }
+        let update = AdmissionConfigUpdate {
+            max_in_flight: Some(2),
+            ..Default::default()
+        };
+        admission.set_config(update.apply(&admission.config()).unwrap());
+        waiting.await.unwrap().unwrap();
+
+        drop(running);
+        let counters = admission.counters();
+        assert_eq!((counters.running, counters.queued, counters.admitted), (0, 0, 2));
+        assert!(admission.state.lock().per_dot.is_empty());
+    }
+
+    #[test]
+    fn test_config_update_and_timeout_header() {
+        let admission = AdmissionController::new(AdmissionConfig::default());
+        let invalid = AdmissionConfigUpdate {
+            max_in_flight: Some(0),
+            ..Default::default()
+        };
+        assert!(matches!(invalid.apply(&admission.config()), Err(ApiError::BadRequest { .. })));
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(admission.timeout(&headers).unwrap(), Duration::from_secs(30));
+        headers.insert(TIMEOUT_HEADER, "250".parse().unwrap());
+        assert_eq!(admission.timeout(&headers).unwrap(), Duration::from_millis(250));
+        headers.insert(TIMEOUT_HEADER, "soon".parse().unwrap());
+        assert!(admission.timeout(&headers).is_err());
+
+        let error = Shed {
+            reason: ShedReason::QueueFull,
+            retry_after: Duration::from_millis(1500),
+        }
+        .to_error("a");
+        let response: hyper::Response<http_body_util::Full<hyper::body::Bytes>> = error.into();
+        assert_eq!(response.status(), hyper::StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["retry-after"], "2");
+    }
+}
@@ -0,0 +1,630 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! API keys for the gateway
+//!
+//! Admins create, list, rotate and revoke keys through `/api/v1/admin/api-keys`;
+//! clients send a key in the [`API_KEY_HEADER`] header instead of a bearer token.
+//! Only the SHA-256 of a secret is stored, in a dotdb collection, and the secret
+//! itself is returned once when the key is created. Each key carries
+//! [`ApiKeyScope`]s and may be restricted to a set of dots; the router checks both
+//! on every request.
+//!
+//! Keys are served from memory and reloaded from the collection once the copy is
+//! older than [`ApiKeyConfig::cache_ttl`], so a key revoked through another gateway
+//! stops working within that time; changes made through this gateway apply at once.
+//! Last-used times are collected in memory and written in batches by a background
+//! task rather than once per request.
+
+use crate::audit;
+use crate::auth::Claims;
+use crate::db::DatabaseClient;
+use crate::error::{ApiError, ApiResult};
+use crate::grpc_pool::parse_env;
+use crate::openapi;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use hyper::Method;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock, RwLockReadGuard};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Header a request carries its API key in
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Start of every secret, so leaked keys are easy to recognise
+const SECRET_PREFIX: &str = "dlk_";
+
+/// Characters of a secret kept to identify the key in listings
+const DISPLAY_PREFIX_LEN: usize = 12;
+
+/// How long the claims of a key-authenticated request are valid for
+const CLAIMS_LIFETIME_MINUTES: i64 = 5;
+
+/// API key settings
+#[derive(Debug, Clone)]
+pub struct ApiKeyConfig {
+    pub enabled: bool,
+    /// dotdb collection the hashed keys are stored in
+    pub collection: String,
+    /// How long keys are served from memory before the collection is read again,
+    /// which bounds how long a key revoked by another gateway keeps working
+    pub cache_ttl: Duration,
+    /// How long a rotated key keeps working alongside its replacement
+    pub rotation_grace: Duration,
+    /// How often collected last-used times are written
+    pub last_used_flush_interval: Duration,
+}
+
+impl Default for ApiKeyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            collection: "api_keys".to_string(),
+            cache_ttl: Duration::from_secs(5),
+            rotation_grace: Duration::from_secs(24 * 60 * 60),
+            last_used_flush_interval: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ApiKeyConfig {
+    /// Load API key settings from `DOTLANTH_API_KEYS_*` variables
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Some(enabled) = parse_env("DOTLANTH_API_KEYS_ENABLED") {
+            config.enabled = enabled;
+        }
+        if let Some(collection) = parse_env::<String>("DOTLANTH_API_KEYS_COLLECTION").filter(|collection| !collection.is_empty()) {
+            config.collection = collection;
+        }
+        if let Some(secs) = parse_env("DOTLANTH_API_KEYS_CACHE_TTL_SECS") {
+            config.cache_ttl = Duration::from_secs(secs);
+        }
+        if let Some(secs) = parse_env("DOTLANTH_API_KEYS_ROTATION_GRACE_SECS") {
+            config.rotation_grace = Duration::from_secs(secs);
+        }
+        if let Some(secs) = parse_env::<u64>("DOTLANTH_API_KEYS_LAST_USED_FLUSH_SECS").filter(|&secs| secs > 0) {
+            config.last_used_flush_interval = Duration::from_secs(secs);
+        }
+
+        config
+    }
+}
+
+/// What a key may be used for; `admin` grants every other scope
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyScope {
+    /// Read-only requests
+    Read,
+    /// Executing dots
+    Execute,
+    /// Deploying and deleting dots
+    Deploy,
+    /// Everything else, including the admin endpoints
+    Admin,
+}
+
+impl ApiKeyScope {
+    /// Scope a key needs to call `method` on `path`
+    pub fn required_for(method: &Method, path: &str) -> Self {
+        let route = openapi::split_version_prefix(path).map_or(path, |(_, route)| route);
+        let segments: Vec<&str> = route.split('/').collect();
+        match (method, segments.as_slice()) {
+            (_, ["", "admin", ..]) => Self::Admin,
+            (&Method::POST, ["", "vm", "dots", "deploy"]) | (&Method::DELETE, ["", "vm", "dots", _]) => Self::Deploy,
+            (&Method::POST, ["", "vm", "dots", _, "execute"]) => Self::Execute,
+            (&Method::GET | &Method::HEAD, _) => Self::Read,
+            _ => Self::Admin,
+        }
+    }
+
+    /// Permissions a bearer token would need for the requests this scope allows
+    fn permissions(self) -> &'static [&'static str] {
+        match self {
+            Self::Read => &["read:documents"],
+            Self::Execute => &["execute:dots"],
+            Self::Deploy => &["deploy:dots"],
+            Self::Admin => &["read:documents", "write:documents", "delete:documents", "execute:dots", "deploy:dots", "admin:users"],
+        }
+    }
+}
+
+impl fmt::Display for ApiKeyScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Read => "read",
+            Self::Execute => "execute",
+            Self::Deploy => "deploy",
+            Self::Admin => "admin",
+        };
+        f.write_str(name)
+    }
+}
+
+/// An API key as listed by the admin endpoints; the secret is never included
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ApiKeyInfo {
+    pub id: String,
+    pub name: String,
+    /// First characters of the secret, enough to tell keys apart
+    pub prefix: String,
+    pub scopes: Vec<ApiKeyScope>,
+    /// Dots the key is restricted to; any dot when absent
+    pub dots: Option<Vec<String>>,
+    /// Principal requests made with the key run as
+    pub owner: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// When the key stops working, set for keys that have been rotated
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// Key that replaced this one
+    pub rotated_to: Option<String>,
+}
+
+impl ApiKeyInfo {
+    /// Whether the key grants `scope`, directly or through `admin`
+    pub fn has_scope(&self, scope: ApiKeyScope) -> bool {
+        self.scopes.contains(&scope) || self.scopes.contains(&ApiKeyScope::Admin)
+    }
+
+    /// Check the key's scopes and dot restriction allow calling `method` on `path`
+    pub fn authorize(&self, method: &Method, path: &str) -> ApiResult<()> {
+        let scope = ApiKeyScope::required_for(method, path);
+        if !self.has_scope(scope) {
+            return Err(ApiError::Forbidden {
+                message: format!("API key '{}' lacks the {} scope", self.name, scope),
+            });
+        }
+        if let (Some(dots), (Some(dot_id), _)) = (&self.dots, audit::targets(path))
+            && !dots.contains(&dot_id)
+        {
+            return Err(ApiError::Forbidden {
+                message: format!("API key '{}' may not access dot {}", self.name, dot_id),
+            });
+        }
+        Ok(())
+    }
+
+    /// Claims a request authenticated with the key runs with: its owner, the admin
+    /// role for admin keys and the permissions its scopes grant
+    pub fn claims(&self) -> Claims {
+        let roles = if self.has_scope(ApiKeyScope::Admin) { vec!["admin".to_string()] } else { Vec::new() };
+        let mut permissions: Vec<String> = self.scopes.iter().flat_map(|scope| scope.permissions()).map(|permission| permission.to_string()).collect();
+        permissions.sort();
+        permissions.dedup();
+        Claims::new(self.owner.clone(), roles, permissions, ChronoDuration::minutes(CLAIMS_LIFETIME_MINUTES))
+    }
+
+    /// Why the key is no longer accepted at `now`, if it is not
+    fn rejection(&self, now: DateTime<Utc>) -> Option<&'static str> {
+        if self.revoked_at.is_some() {
+            Some("API key has been revoked")
+        } else if self.expires_at.is_some_and(|expires_at| expires_at <= now) {
+            Some("API key has expired")
+        } else {
+            None
+        }
+    }
+}
+
+/// Body of a request creating a key
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+    /// Restrict the key to these dots
+    #[serde(default)]
+    pub dots: Option<Vec<String>>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// A newly created key and its secret, which is not shown again
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CreatedApiKey {
+    pub secret: String,
+    pub key: ApiKeyInfo,
+}
+
+/// A key as stored in the collection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredKey {
+    /// Hex SHA-256 of the secret
+    key_hash: String,
+    #[serde(flatten)]
+    info: ApiKeyInfo,
+}
+
+#[derive(Debug, Clone)]
+struct CachedKey {
+    document_id: String,
+    stored: StoredKey,
+}
+
+#[derive(Debug, Default)]
+struct KeyCache {
+    /// Keys by the hash of their secret
+    keys: HashMap<String, CachedKey>,
+    loaded_at: Option<Instant>,
+}
+
+/// Stores API keys and authenticates the requests that present them
+pub struct ApiKeyStore {
+    config: ApiKeyConfig,
+    db_client: DatabaseClient,
+    cache: RwLock<KeyCache>,
+    /// Last-used times not yet written, by key hash
+    last_used: parking_lot::Mutex<HashMap<String, DateTime<Utc>>>,
+    /// Serializes read-modify-write updates so one cannot undo another, such as a
+    /// last-used flush overwriting a revocation
+    writes: Mutex<()>,
+}
+
+impl ApiKeyStore {
+    pub fn new(config: ApiKeyConfig, db_client: DatabaseClient) -> Self {
+        Self {
+            config,
+            db_client,
+            cache: RwLock::new(KeyCache::default()),
+            last_used: parking_lot::Mutex::new(HashMap::new()),
+            writes: Mutex::new(()),
+        }
+    }
+
+    pub fn config(&self) -> &ApiKeyConfig {
+        &self.config
+    }
+
+    /// The key `secret` belongs to, if it exists and is still accepted
+    pub async fn authenticate(&self, secret: &str) -> ApiResult<ApiKeyInfo> {
+        let key_hash = hash_secret(secret);
+        let info = self.fresh_cache().await?.keys.get(&key_hash).map(|key| key.stored.info.clone());
+        let info = info.ok_or_else(|| ApiError::Unauthorized {
+            message: "Invalid API key".to_string(),
+        })?;
+
+        let now = Utc::now();
+        if let Some(reason) = info.rejection(now) {
+            return Err(ApiError::Unauthorized { message: reason.to_string() });
+        }
+        self.last_used.lock().insert(key_hash, now);
+        Ok(info)
+    }
+
+    /// Create a key owned by `owner`, returning it with its secret
+    pub async fn create(&self, owner: &str, request: CreateApiKeyRequest) -> ApiResult<CreatedApiKey> {
+        let name = request.name.trim();
+        if name.is_empty() {
+            return Err(ApiError::BadRequest {
+                message: "API key name must not be empty".to_string(),
+            });
+        }
+        if request.scopes.is_empty() {
+            return Err(ApiError::BadRequest {
+                message: "API key needs at least one scope".to_string(),
+            });
+        }
+        if request.dots.as_ref().is_some_and(|dots| dots.is_empty()) {
+            return Err(ApiError::BadRequest {
+                message: "API key dot restriction must name at least one dot".to_string(),
+            });
+        }
+
+        let mut scopes = request.scopes;
+        scopes.sort();
+        scopes.dedup();
+        let info = ApiKeyInfo {
+            id: Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            prefix: String::new(),
+            scopes,
+            dots: request.dots,
+            owner: owner.to_string(),
+            created_at: Utc::now(),
+            last_used_at: None,
+            expires_at: request.expires_at,
+            revoked_at: None,
+            rotated_to: None,
+        };
+
+        let _writes = self.writes.lock().await;
+        let created = self.insert(info).await?;
+        info!("Created API key {} ({}) for {}", created.key.id, created.key.name, owner);
+        Ok(created)
+    }
+
+    /// Every key, oldest first, including last-used times not yet written
+    pub async fn list(&self) -> ApiResult<Vec<ApiKeyInfo>> {
+        let pending = self.last_used.lock().clone();
+        let mut keys: Vec<ApiKeyInfo> = self
+            .fresh_cache()
+            .await?
+            .keys
+            .iter()
+            .map(|(key_hash, key)| {
+                let mut info = key.stored.info.clone();
+                if let Some(&used_at) = pending.get(key_hash) {
+                    info.last_used_at = Some(used_at);
+                }
+                info
+            })
+            .collect();
+        keys.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+        Ok(keys)
+    }
+
+    /// Replace a key with a new secret carrying the same scopes and dots. The old key
+    /// keeps working for [`ApiKeyConfig::rotation_grace`] so clients can switch over.
+    pub async fn rotate(&self, id: &str) -> ApiResult<CreatedApiKey> {
+        let _writes = self.writes.lock().await;
+        let mut old = self.find(id).await?;
+        let now = Utc::now();
+        if let Some(reason) = old.stored.info.rejection(now) {
+            return Err(ApiError::Conflict {
+                message: format!("{}, it cannot be rotated", reason),
+            });
+        }
+        if let Some(rotated_to) = &old.stored.info.rotated_to {
+            return Err(ApiError::Conflict {
+                message: format!("API key {} has already been rotated to {}", id, rotated_to),
+            });
+        }
+
+        let replacement = ApiKeyInfo {
+            id: Uuid::new_v4().to_string(),
+            created_at: now,
+            last_used_at: None,
+            ..old.stored.info.clone()
+        };
+        let created = self.insert(replacement).await?;
+
+        let grace_ends = now + ChronoDuration::from_std(self.config.rotation_grace).unwrap_or(ChronoDuration::MAX);
+        let old_info = &mut old.stored.info;
+        old_info.expires_at = Some(old_info.expires_at.map_or(grace_ends, |expires_at| expires_at.min(grace_ends)));
+        old_info.rotated_to = Some(created.key.id.clone());
+        self.write(old).await?;
+
+        info!("Rotated API key {} to {}", id, created.key.id);
+        Ok(created)
+    }
+
+    /// Revoke a key. This gateway rejects it at once, others within the cache TTL.
+    pub async fn revoke(&self, id: &str) -> ApiResult<ApiKeyInfo> {
+        let _writes = self.writes.lock().await;
+        let mut key = self.find(id).await?;
+        if key.stored.info.revoked_at.is_none() {
+            key.stored.info.revoked_at = Some(Utc::now());
+            key = self.write(key).await?;
+            info!("Revoked API key {}", id);
+        }
+        Ok(key.stored.info)
+    }
+
+    /// Write the last-used times collected since the previous flush, one update per key
+    pub async fn flush_last_used(&self) {
+        let pending = std::mem::take(&mut *self.last_used.lock());
+        if pending.is_empty() {
+            return;
+        }
+
+        let _writes = self.writes.lock().await;
+        for (key_hash, used_at) in pending {
+            let Some(mut key) = self.cache.read().await.keys.get(&key_hash).cloned() else {
+                continue;
+            };
+            if key.stored.info.last_used_at.is_some_and(|last_used_at| last_used_at >= used_at) {
+                continue;
+            }
+            key.stored.info.last_used_at = Some(used_at);
+            let id = key.stored.info.id.clone();
+            if let Err(e) = self.write(key).await {
+                warn!("Failed to record last use of API key {}: {}", id, e);
+            }
+        }
+    }
+
+    /// Spawn the task that writes last-used times every
+    /// [`ApiKeyConfig::last_used_flush_interval`]
+    pub fn spawn_last_used_flusher(self: &Arc<Self>) -> JoinHandle<()> {
+        let store = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(store.config.last_used_flush_interval);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                store.flush_last_used().await;
+            }
+        })
+    }
+
+    /// The cached keys, reloaded from the collection first if they are older than the TTL
+    async fn fresh_cache(&self) -> ApiResult<RwLockReadGuard<'_, KeyCache>> {
+        let is_fresh = |cache: &KeyCache| cache.loaded_at.is_some_and(|loaded_at| loaded_at.elapsed() < self.config.cache_ttl);
+
+        let cache = self.cache.read().await;
+        if is_fresh(&cache) {
+            return Ok(cache);
+        }
+        drop(cache);
+
+        let mut cache = self.cache.write().await;
+        if !is_fresh(&cache) {
+            *cache = self.load().await?;
+        }
+        Ok(cache.downgrade())
+    }
+
+    async fn load(&self) -> ApiResult<KeyCache> {
+        let documents = self.db_client.scan_documents(&self.config.collection).await?;
+        let mut keys = HashMap::with_capacity(documents.len());
+        for (document_id, content) in documents {
+            match serde_json::from_value::<StoredKey>(content) {
+                Ok(stored) => {
+                    keys.insert(stored.key_hash.clone(), CachedKey { document_id, stored });
+                }
+                Err(e) => warn!("Ignoring malformed API key document {}: {}", document_id, e),
+            }
+        }
+        Ok(KeyCache {
+            keys,
+            loaded_at: Some(Instant::now()),
+        })
+    }
+
+    async fn find(&self, id: &str) -> ApiResult<CachedKey> {
+        let cache = self.fresh_cache().await?;
+        cache.keys.values().find(|key| key.stored.info.id == id).cloned().ok_or_else(|| ApiError::NotFound {
+            message: format!("API key {} not found", id),
+        })
+    }
+
+    /// Store a new key under a fresh secret
+    async fn insert(&self, mut info: ApiKeyInfo) -> ApiResult<CreatedApiKey> {
+        let secret = generate_secret()?;
+        info.prefix = secret[..DISPLAY_PREFIX_LEN].to_string();
+        let stored = StoredKey { key_hash: hash_secret(&secret), info };
+
+        self.db_client.ensure_collection(&self.config.collection).await?;
+        let document_id = self.db_client.create_document(&self.config.collection, serde_json::to_value(&stored)?).await?.id;
+
+        let key = stored.info.clone();
+        self.cache.write().await.keys.insert(stored.key_hash.clone(), CachedKey { document_id, stored });
+        Ok(CreatedApiKey { secret, key })
+    }
+
+    /// Store an updated key and serve it from the cache
+    async fn write(&self, key: CachedKey) -> ApiResult<CachedKey> {
+        self.db_client.update_document(&self.config.collection, &key.document_id, serde_json::to_value(&key.stored)?).await?;
+        self.cache.write().await.keys.insert(key.stored.key_hash.clone(), key.clone());
+        Ok(key)
+    }
+}
+
+fn generate_secret() -> ApiResult<String> {
+    let mut bytes = [0u8; 32];
+    SystemRandom::new().fill(&mut bytes).map_err(|_| ApiError::InternalServerError {
+        message: "Failed to generate API key".to_string(),
+    })?;
+    Ok(format!("{}{}", SECRET_PREFIX, URL_SAFE_NO_PAD.encode(bytes)))
+}
+
+fn hash_secret(secret: &str) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, secret.as_bytes());
+    digest.as_ref().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(config: ApiKeyConfig) -> ApiKeyStore {
+        ApiKeyStore::new(config, DatabaseClient::new("").unwrap())
+    }
+
+    fn request(name: &str, scopes: &[ApiKeyScope], dots: Option<&[&str]>) -> CreateApiKeyRequest {
+        CreateApiKeyRequest {
+            name: name.to_string(),
+            scopes: scopes.to_vec(),
+            dots: dots.map(|dots| dots.iter().map(|dot| dot.to_string()).collect()),
+            expires_at: None,
+        }
+    }
+
+    #[test]
+    fn test_required_scopes() {
+        assert_eq!(ApiKeyScope::required_for(&Method::GET, "/api/v1/vm/dots"), ApiKeyScope::Read);
+        assert_eq!(ApiKeyScope::required_for(&Method::POST, "/api/v1/vm/dots/abc/execute"), ApiKeyScope::Execute);
+        assert_eq!(ApiKeyScope::required_for(&Method::POST, "/api/v2/vm/dots/deploy"), ApiKeyScope::Deploy);
+        assert_eq!(ApiKeyScope::required_for(&Method::DELETE, "/api/v1/vm/dots/abc"), ApiKeyScope::Deploy);
+        assert_eq!(ApiKeyScope::required_for(&Method::POST, "/api/v1/collections/users/documents"), ApiKeyScope::Admin);
+        assert_eq!(ApiKeyScope::required_for(&Method::GET, "/api/v1/admin/api-keys"), ApiKeyScope::Admin);
+    }
+
+    #[tokio::test]
+    async fn test_scopes_and_dot_restriction_are_enforced() {
+        let store = store(ApiKeyConfig::default());
+        let created = store.create("admin", request("runner", &[ApiKeyScope::Execute, ApiKeyScope::Read], Some(&["abc"]))).await.unwrap();
+        assert!(created.secret.starts_with(SECRET_PREFIX));
+        assert!(created.secret.starts_with(&created.key.prefix));
+
+        let key = store.authenticate(&created.secret).await.unwrap();
+        assert!(key.authorize(&Method::POST, "/api/v1/vm/dots/abc/execute").is_ok());
+        assert!(key.authorize(&Method::GET, "/api/v1/vm/dots").is_ok());
+        assert!(matches!(key.authorize(&Method::POST, "/api/v1/vm/dots/other/execute"), Err(ApiError::Forbidden { .. })));
+        assert!(matches!(key.authorize(&Method::POST, "/api/v1/vm/dots/deploy"), Err(ApiError::Forbidden { .. })));
+
+        let claims = key.claims();
+        assert_eq!(claims.sub, "admin");
+        assert!(claims.has_permission("execute:dots"));
+        assert!(!claims.has_permission("deploy:dots"));
+        assert!(!claims.has_role("admin"));
+
+        assert!(matches!(store.authenticate("dlk_unknown").await, Err(ApiError::Unauthorized { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_revoked_keys_are_rejected_by_other_stores_after_the_ttl() {
+        let db_client = DatabaseClient::new("").unwrap();
+        let config = ApiKeyConfig {
+            cache_ttl: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let local = ApiKeyStore::new(config.clone(), db_client.clone());
+        let remote = ApiKeyStore::new(config, db_client);
+
+        let created = local.create("admin", request("ci", &[ApiKeyScope::Read], None)).await.unwrap();
+        assert!(remote.authenticate(&created.secret).await.is_ok());
+
+        local.revoke(&created.key.id).await.unwrap();
+        assert!(matches!(local.authenticate(&created.secret).await, Err(ApiError::Unauthorized { .. })));
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert!(matches!(remote.authenticate(&created.secret).await, Err(ApiError::Unauthorized { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_last_used_times_are_written_in_batches() {
+        let db_client = DatabaseClient::new("").unwrap();
+        let store = ApiKeyStore::new(ApiKeyConfig::default(), db_client.clone());
+        let created = store.create("admin", request("ci", &[ApiKeyScope::Read], None)).await.unwrap();
+        for _ in 0..3 {
+            store.authenticate(&created.secret).await.unwrap();
+        }
+
+        let stored_last_used = || async {
+            let (_, content) = db_client.scan_documents("api_keys").await.unwrap().pop().unwrap();
+            serde_json::from_value::<StoredKey>(content).unwrap().info.last_used_at
+        };
+        assert_eq!(stored_last_used().await, None);
+        assert!(store.list().await.unwrap()[0].last_used_at.is_some());
+
+        store.flush_last_used().await;
+        assert!(stored_last_used().await.is_some());
+        assert!(store.last_used.lock().is_empty());
+    }
+}
@@ -0,0 +1,542 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Audit log of mutating API operations
+//!
+//! The router records one [`AuditRecord`] for every POST, PUT, PATCH and DELETE
+//! under `/api/`, which includes dot deployment and execution. Records go onto a
+//! bounded queue drained by a background writer that stores them in a dotdb
+//! collection and, optionally, appends them to a JSONL file. A full queue drops the
+//! record and counts it rather than holding up the request.
+//!
+//! Request bodies are never stored: handlers read them through [`read_body`], which
+//! records their SHA-256 so it can be folded into the record's request hash.
+
+use crate::auth::Claims;
+use crate::db::DatabaseClient;
+use crate::error::ApiResult;
+use crate::grpc_pool::parse_env;
+use crate::openapi;
+use crate::rate_limiting::api_key_fingerprint;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use http_body_util::BodyExt;
+use hyper::body::{Bytes, Incoming};
+use hyper::{Method, Request, StatusCode};
+use ring::digest::{Context, SHA256};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{Mutex, Notify, mpsc};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// How often records older than the retention period are deleted
+const RETENTION_SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Audit log settings
+#[derive(Debug, Clone)]
+pub struct AuditConfig {
+    pub enabled: bool,
+    /// dotdb collection the records are written to
+    pub collection: String,
+    /// JSONL file records are also appended to
+    pub file_path: Option<PathBuf>,
+    /// Records waiting to be written before new ones are dropped
+    pub queue_capacity: usize,
+    /// Days records are kept; 0 keeps them forever
+    pub retention_days: u64,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            collection: "audit_log".to_string(),
+            file_path: None,
+            queue_capacity: 10_000,
+            retention_days: 90,
+        }
+    }
+}
+
+impl AuditConfig {
+    /// Load audit settings from `DOTLANTH_AUDIT_*` variables
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Some(enabled) = parse_env("DOTLANTH_AUDIT_ENABLED") {
+            config.enabled = enabled;
+        }
+        if let Some(collection) = parse_env::<String>("DOTLANTH_AUDIT_COLLECTION").filter(|collection| !collection.is_empty()) {
+            config.collection = collection;
+        }
+        config.file_path = parse_env::<PathBuf>("DOTLANTH_AUDIT_FILE");
+        if let Some(capacity) = parse_env::<usize>("DOTLANTH_AUDIT_QUEUE_CAPACITY").filter(|&capacity| capacity > 0) {
+            config.queue_capacity = capacity;
+        }
+        if let Some(days) = parse_env("DOTLANTH_AUDIT_RETENTION_DAYS") {
+            config.retention_days = days;
+        }
+
+        config
+    }
+}
+
+/// One mutating request as seen by the gateway
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AuditRecord {
+    pub id: String,
+    /// When the request arrived
+    pub timestamp: DateTime<Utc>,
+    /// Subject of the bearer token, if the request was authenticated
+    pub principal: Option<String>,
+    /// Fingerprint of the `x-api-key` header, never the key itself
+    pub api_key_id: Option<String>,
+    pub method: String,
+    /// Route template the request matched, or its path if it matched none
+    pub route: String,
+    pub path: String,
+    pub dot_id: Option<String>,
+    pub collection: Option<String>,
+    /// SHA-256 over the method, path, query and body
+    pub request_hash: String,
+    pub status: u16,
+    pub latency_ms: u64,
+}
+
+/// Filters for [`AuditLogger::query`]
+#[derive(Debug, Clone, Default)]
+pub struct AuditQuery {
+    /// Earliest timestamp, inclusive
+    pub from: Option<DateTime<Utc>>,
+    /// Latest timestamp, exclusive
+    pub to: Option<DateTime<Utc>>,
+    pub principal: Option<String>,
+    /// Most records to return
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    fn matches(&self, record: &AuditRecord) -> bool {
+        self.from.is_none_or(|from| record.timestamp >= from)
+            && self.to.is_none_or(|to| record.timestamp < to)
+            && self.principal.as_ref().is_none_or(|principal| record.principal.as_ref() == Some(principal))
+    }
+}
+
+/// Whether requests with this method and path are audited
+pub fn is_audited(method: &Method, path: &str) -> bool {
+    path.starts_with("/api/") && matches!(*method, Method::POST | Method::PUT | Method::PATCH | Method::DELETE)
+}
+
+/// SHA-256 of a request body, filled in by [`read_body`] or by handlers streaming the body
+#[derive(Debug, Clone, Default)]
+pub struct BodyDigest(Arc<OnceLock<Vec<u8>>>);
+
+impl BodyDigest {
+    fn record(&self, body: &[u8]) {
+        self.record_digest(ring::digest::digest(&SHA256, body).as_ref().to_vec());
+    }
+
+    /// Record the digest of a body that was streamed rather than read whole
+    pub fn record_digest(&self, digest: Vec<u8>) {
+        let _ = self.0.set(digest);
+    }
+}
+
+/// Read a request's body, recording its digest if the request is being audited
+pub async fn read_body(req: Request<Incoming>) -> ApiResult<Bytes> {
+    let (parts, body) = req.into_parts();
+    let body = body.collect().await?.to_bytes();
+    if let Some(digest) = parts.extensions.get::<BodyDigest>() {
+        digest.record(&body);
+    }
+    Ok(body)
+}
+
+/// An audited request that has not finished yet
+pub struct PendingAudit {
+    started: Instant,
+    timestamp: DateTime<Utc>,
+    principal: Option<String>,
+    api_key_id: Option<String>,
+    method: Method,
+    path: String,
+    query: String,
+    body: BodyDigest,
+}
+
+impl PendingAudit {
+    fn new<B>(req: &Request<B>) -> Self {
+        Self {
+            started: Instant::now(),
+            timestamp: Utc::now(),
+            principal: None,
+            api_key_id: req.headers().get("x-api-key").and_then(|v| v.to_str().ok()).map(api_key_fingerprint),
+            method: req.method().clone(),
+            path: req.uri().path().to_string(),
+            query: req.uri().query().unwrap_or("").to_string(),
+            body: BodyDigest::default(),
+        }
+    }
+
+    /// Pick up the authenticated principal and let the handler record the body digest
+    pub fn attach<B>(&mut self, req: &mut Request<B>) {
+        self.principal = req.extensions().get::<Claims>().map(|claims| claims.sub.clone());
+        req.extensions_mut().insert(self.body.clone());
+    }
+
+    fn finish(self, status: StatusCode) -> AuditRecord {
+        let mut hash = Context::new(&SHA256);
+        hash.update(self.method.as_str().as_bytes());
+        hash.update(b" ");
+        hash.update(self.path.as_bytes());
+        hash.update(b"?");
+        hash.update(self.query.as_bytes());
+        hash.update(b"\n");
+        hash.update(self.body.0.get().map(Vec::as_slice).unwrap_or_default());
+
+        let (dot_id, collection) = targets(&self.path);
+        AuditRecord {
+            id: Uuid::new_v4().to_string(),
+            timestamp: self.timestamp,
+            principal: self.principal,
+            api_key_id: self.api_key_id,
+            method: self.method.to_string(),
+            route: openapi::find_route(&self.method, &self.path).map(|route| route.full_path()).unwrap_or_else(|| self.path.clone()),
+            path: self.path,
+            dot_id,
+            collection,
+            request_hash: hex(hash.finish().as_ref()),
+            status: status.as_u16(),
+            latency_ms: self.started.elapsed().as_millis() as u64,
+        }
+    }
+}
+
+/// The dot and collection a request path targets
+pub(crate) fn targets(path: &str) -> (Option<String>, Option<String>) {
+    let segments: Vec<&str> = path.split('/').collect();
+    match segments.as_slice() {
+        ["", "api", _, "vm", "dots", "deploy", ..] => (None, None),
+        ["", "api", _, "vm", "dots", id, ..] if !id.is_empty() => (Some(id.to_string()), None),
+        ["", "api", _, "collections", collection, ..] if !collection.is_empty() => (None, Some(collection.to_string())),
+        _ => (None, None),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Queues audit records and writes them in the background
+pub struct AuditLogger {
+    config: AuditConfig,
+    db_client: DatabaseClient,
+    sender: mpsc::Sender<AuditRecord>,
+    receiver: Mutex<Option<mpsc::Receiver<AuditRecord>>>,
+    closing: Notify,
+    dropped: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl AuditLogger {
+    pub fn new(config: AuditConfig, db_client: DatabaseClient) -> Self {
+        let (sender, receiver) = mpsc::channel(config.queue_capacity.max(1));
+        Self {
+            config,
+            db_client,
+            sender,
+            receiver: Mutex::new(Some(receiver)),
+            closing: Notify::new(),
+            dropped: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+        }
+    }
+
+    pub fn config(&self) -> &AuditConfig {
+        &self.config
+    }
+
+    /// Start auditing `req`, or `None` if it is not a mutating API request
+    pub fn begin<B>(&self, req: &Request<B>) -> Option<PendingAudit> {
+        (self.config.enabled && is_audited(req.method(), req.uri().path())).then(|| PendingAudit::new(req))
+    }
+
+    /// Queue the record for a finished request
+    pub fn finish(&self, pending: PendingAudit, status: StatusCode) {
+        self.submit(pending.finish(status));
+    }
+
+    fn submit(&self, record: AuditRecord) {
+        if self.sender.try_send(record).is_err() {
+            let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+            if dropped.is_power_of_two() {
+                warn!("Audit queue full, {} records dropped so far", dropped);
+            }
+        }
+    }
+
+    /// Records dropped because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Records that could not be written to a sink
+    pub fn failed(&self) -> u64 {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    /// Spawn the task that writes queued records and deletes expired ones
+    ///
+    /// Only the first call starts a writer. It runs until [`close`](Self::close).
+    pub fn spawn_writer(self: &Arc<Self>) -> JoinHandle<()> {
+        let logger = self.clone();
+        tokio::spawn(async move {
+            let Some(mut receiver) = logger.receiver.lock().await.take() else {
+                return;
+            };
+            if let Err(e) = logger.db_client.ensure_collection(&logger.config.collection).await {
+                warn!("Failed to create audit collection {}: {}", logger.config.collection, e);
+            }
+            let mut file = match &logger.config.file_path {
+                Some(path) => open_log_file(path).await,
+                None => None,
+            };
+
+            let mut retention = tokio::time::interval(RETENTION_SWEEP_INTERVAL);
+            loop {
+                tokio::select! {
+                    record = receiver.recv() => match record {
+                        Some(record) => logger.write(&record, &mut file).await,
+                        None => break,
+                    },
+                    _ = logger.closing.notified() => receiver.close(),
+                    _ = retention.tick(), if logger.config.retention_days > 0 => {
+                        let cutoff = Utc::now() - ChronoDuration::days(logger.config.retention_days as i64);
+                        logger.purge_before(cutoff, &mut file).await;
+                    }
+                }
+            }
+            info!("Audit writer stopped");
+        })
+    }
+
+    /// Stop accepting records; the writer finishes once the queue is empty
+    pub fn close(&self) {
+        self.closing.notify_one();
+    }
+
+    async fn write(&self, record: &AuditRecord, file: &mut Option<File>) {
+        let value = match serde_json::to_value(record) {
+            Ok(value) => value,
+            Err(e) => {
+                warn!("Failed to serialize audit record {}: {}", record.id, e);
+                self.failed.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        };
+
+        if let Some(handle) = file {
+            let mut line = value.to_string();
+            line.push('\n');
+            if let Err(e) = handle.write_all(line.as_bytes()).await {
+                warn!("Failed to append audit record {} to file: {}", record.id, e);
+                self.failed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        if let Err(e) = self.db_client.create_document(&self.config.collection, value).await {
+            warn!("Failed to store audit record {}: {}", record.id, e);
+            self.failed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Delete records older than `cutoff` from the collection and the file
+    async fn purge_before(&self, cutoff: DateTime<Utc>, file: &mut Option<File>) {
+        let mut purged = 0;
+        match self.db_client.scan_documents(&self.config.collection).await {
+            Ok(documents) => {
+                for (id, content) in documents {
+                    let expired = serde_json::from_value::<AuditRecord>(content).is_ok_and(|record| record.timestamp < cutoff);
+                    if expired && self.db_client.delete_document(&self.config.collection, &id).await.is_ok() {
+                        purged += 1;
+                    }
+                }
+            }
+            Err(e) => warn!("Failed to scan audit collection for expired records: {}", e),
+        }
+
+        if let Some(path) = &self.config.file_path {
+            // Close the append handle while the file is rewritten
+            file.take();
+            if let Err(e) = purge_file(path, cutoff).await {
+                warn!("Failed to purge expired records from {}: {}", path.display(), e);
+            }
+            *file = open_log_file(path).await;
+        }
+
+        if purged > 0 {
+            info!("Deleted {} audit records older than {}", purged, cutoff.to_rfc3339());
+        }
+    }
+
+    /// Stored records matching `query`, newest first
+    pub async fn query(&self, query: &AuditQuery) -> ApiResult<Vec<AuditRecord>> {
+        let documents = self.db_client.scan_documents(&self.config.collection).await?;
+        let mut records: Vec<AuditRecord> = documents
+            .into_iter()
+            .filter_map(|(_, content)| serde_json::from_value(content).ok())
+            .filter(|record| query.matches(record))
+            .collect();
+        records.sort_by_key(|record| std::cmp::Reverse(record.timestamp));
+        if let Some(limit) = query.limit {
+            records.truncate(limit);
+        }
+        Ok(records)
+    }
+}
+
+async fn open_log_file(path: &Path) -> Option<File> {
+    match OpenOptions::new().create(true).append(true).open(path).await {
+        Ok(file) => Some(file),
+        Err(e) => {
+            warn!("Failed to open audit log file {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Rewrite a JSONL audit file without the records older than `cutoff`
+async fn purge_file(path: &Path, cutoff: DateTime<Utc>) -> std::io::Result<()> {
+    let contents = match tokio::fs::read_to_string(path).await {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let kept: String = contents
+        .lines()
+        .filter(|line| !matches!(serde_json::from_str::<AuditRecord>(line), Ok(record) if record.timestamp < cutoff))
+        .flat_map(|line| [line, "\n"])
+        .collect();
+    if kept.len() == contents.len() {
+        return Ok(());
+    }
+
+    let temp = path.with_extension("jsonl.tmp");
+    tokio::fs::write(&temp, kept).await?;
+    tokio::fs::rename(&temp, path).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(principal: &str, timestamp: DateTime<Utc>) -> AuditRecord {
+        let mut pending = PendingAudit::new(&Request::post("/api/v1/collections/users/documents").body(()).unwrap());
+        pending.principal = Some(principal.to_string());
+        pending.timestamp = timestamp;
+        pending.finish(StatusCode::CREATED)
+    }
+
+    #[test]
+    fn test_only_mutating_api_requests_are_audited() {
+        assert!(is_audited(&Method::POST, "/api/v1/vm/dots/abc/execute"));
+        assert!(is_audited(&Method::DELETE, "/api/v1/collections/users"));
+        assert!(!is_audited(&Method::GET, "/api/v1/collections"));
+        assert!(!is_audited(&Method::POST, "/graphql"));
+    }
+
+    #[test]
+    fn test_record_hashes_body_and_extracts_targets() {
+        let mut req = Request::put("/api/v1/collections/users/documents/42?upsert=true").header("x-api-key", "secret-key").body(()).unwrap();
+        let mut pending = PendingAudit::new(&req);
+        pending.attach(&mut req);
+        req.extensions().get::<BodyDigest>().unwrap().record(br#"{"password":"hunter2"}"#);
+
+        let record = pending.finish(StatusCode::OK);
+        assert_eq!(record.route, "/api/v1/collections/{collection}/documents/{id}");
+        assert_eq!(record.collection.as_deref(), Some("users"));
+        assert_eq!(record.api_key_id, Some(api_key_fingerprint("secret-key")));
+        assert_eq!(record.request_hash.len(), 64);
+        assert!(!serde_json::to_string(&record).unwrap().contains("hunter2"));
+
+        let other = PendingAudit::new(&req);
+        other.body.record(br#"{"password":"other"}"#);
+        assert_ne!(other.finish(StatusCode::OK).request_hash, record.request_hash);
+
+        assert_eq!(targets("/api/v1/vm/dots/abc/execute"), (Some("abc".to_string()), None));
+        assert_eq!(targets("/api/v1/vm/dots/deploy"), (None, None));
+    }
+
+    #[tokio::test]
+    async fn test_queue_overflow_drops_and_counts() {
+        let db_client = DatabaseClient::new("").unwrap();
+        let logger = AuditLogger::new(
+            AuditConfig {
+                queue_capacity: 2,
+                ..Default::default()
+            },
+            db_client,
+        );
+        for _ in 0..5 {
+            logger.submit(record("admin", Utc::now()));
+        }
+        assert_eq!(logger.dropped(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_writer_stores_filters_and_purges() {
+        let db_client = DatabaseClient::new("").unwrap();
+        let path = std::env::temp_dir().join(format!("audit-{}.jsonl", Uuid::new_v4()));
+        // Expired records are purged explicitly below rather than by the writer's sweep
+        let config = AuditConfig {
+            file_path: Some(path.clone()),
+            retention_days: 0,
+            ..Default::default()
+        };
+        let logger = Arc::new(AuditLogger::new(config, db_client));
+        let writer = logger.spawn_writer();
+
+        let now = Utc::now();
+        logger.submit(record("admin", now - ChronoDuration::days(100)));
+        logger.submit(record("admin", now));
+        logger.submit(record("user", now));
+        logger.close();
+        writer.await.unwrap();
+
+        let all = logger.query(&AuditQuery::default()).await.unwrap();
+        assert_eq!(all.len(), 3);
+        let query = AuditQuery {
+            from: Some(now - ChronoDuration::days(1)),
+            principal: Some("admin".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(logger.query(&query).await.unwrap().len(), 1);
+
+        let mut file = open_log_file(&path).await;
+        logger.purge_before(now - ChronoDuration::days(90), &mut file).await;
+        assert_eq!(logger.query(&AuditQuery::default()).await.unwrap().len(), 2);
+        assert_eq!(tokio::fs::read_to_string(&path).await.unwrap().lines().count(), 2);
+        let _ = tokio::fs::remove_file(&path).await;
+    }
+}
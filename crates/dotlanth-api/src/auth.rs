@@ -240,6 +240,13 @@ impl AuthService {
         self.jwt_manager.validate_token(token)
     }
 
+    /// Sign a short-lived token and validate it, confirming tokens can be checked at all
+    pub fn self_check(&self) -> ApiResult<()> {
+        let claims = Claims::new("readiness-probe".to_string(), Vec::new(), Vec::new(), Duration::seconds(60));
+        let token = self.jwt_manager.create_token(&claims)?;
+        self.jwt_manager.validate_token(&token).map(|_| ())
+    }
+
     /// Get user profile by user ID
     pub fn get_user_profile(&self, user_id: &str) -> ApiResult<UserProfile> {
         let user = self.users.get(user_id).ok_or_else(|| ApiError::NotFound {
@@ -0,0 +1,155 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Runs REST contract cases against a live gateway
+//!
+//! ```text
+//! cargo run -p dotlanth-api --bin compat-check -- --target http://localhost:8080
+//! ```
+//!
+//! Exits with 0 when every case passed or was skipped, 1 when a case failed and 2
+//! when the run could not start.
+
+use clap::Parser;
+use dotlanth_api::compatibility_testing::contract::{ContractCase, load_cases};
+use dotlanth_api::compatibility_testing::runner::{ContractRunner, DEFAULT_REQUEST_TIMEOUT, RunnerConfig};
+use dotlanth_api::compatibility_testing::suite::builtin_cases;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+/// Environment variable the bearer token is read from when --token is not given
+const TOKEN_ENV: &str = "DOTLANTH_API_TOKEN";
+
+/// Check a gateway against the REST API contract
+#[derive(Parser, Debug)]
+#[command(name = "compat-check")]
+struct Cli {
+    /// Base URL of the gateway
+    #[arg(long, default_value = "http://localhost:8080")]
+    target: String,
+
+    /// Bearer token for authenticated routes (defaults to $DOTLANTH_API_TOKEN)
+    #[arg(long)]
+    token: Option<String>,
+
+    /// Capabilities of the target, replacing those it reports at /api/v1/version
+    #[arg(long, value_delimiter = ',')]
+    capabilities: Option<Vec<String>>,
+
+    /// Treat a capability as offered, e.g. abi_registry (repeatable)
+    #[arg(long = "with")]
+    with: Vec<String>,
+
+    /// Treat a capability as missing (repeatable)
+    #[arg(long)]
+    without: Vec<String>,
+
+    /// Run the cases in a YAML or JSON file instead of the built-in ones (repeatable)
+    #[arg(long = "cases")]
+    cases: Vec<PathBuf>,
+
+    /// Set a case variable, e.g. abi_dot=my-dot (repeatable)
+    #[arg(long = "var", value_parser = parse_var)]
+    vars: Vec<(String, String)>,
+
+    /// Skip cases that write to the target
+    #[arg(long)]
+    restricted: bool,
+
+    /// Write the JSON report to this file
+    #[arg(long)]
+    json: Option<PathBuf>,
+
+    /// Write the JUnit XML report to this file
+    #[arg(long)]
+    junit: Option<PathBuf>,
+
+    /// Seconds each request may take
+    #[arg(long, default_value_t = DEFAULT_REQUEST_TIMEOUT.as_secs())]
+    timeout: u64,
+}
+
+fn parse_var(var: &str) -> Result<(String, String), String> {
+    var.split_once('=')
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected name=value, got {}", var))
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let cases = if cli.cases.is_empty() {
+        builtin_cases()
+    } else {
+        let mut cases: Vec<ContractCase> = Vec::new();
+        for path in &cli.cases {
+            match load_cases(path) {
+                Ok(loaded) => cases.extend(loaded),
+                Err(e) => {
+                    eprintln!("compat-check: {}", e);
+                    return ExitCode::from(2);
+                }
+            }
+        }
+        cases
+    };
+
+    let mut config = RunnerConfig::new(cli.target);
+    config.token = cli.token.or_else(|| std::env::var(TOKEN_ENV).ok());
+    config.capabilities = cli.capabilities;
+    config.with = cli.with;
+    config.without = cli.without;
+    config.restricted = cli.restricted;
+    config.vars = cli.vars.into_iter().collect::<BTreeMap<_, _>>();
+    config.request_timeout = Duration::from_secs(cli.timeout);
+
+    let runner = match ContractRunner::new(config) {
+        Ok(runner) => runner,
+        Err(e) => {
+            eprintln!("compat-check: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+    let report = runner.run(&cases).await;
+
+    for case in &report.cases {
+        match case.skip_reason.as_deref() {
+            Some(reason) => println!("SKIP {} ({})", case.id, reason),
+            None if case.failures.is_empty() => println!("PASS {}", case.id),
+            None => {
+                println!("FAIL {}", case.id);
+                for failure in &case.failures {
+                    println!("     {}", failure);
+                }
+            }
+        }
+    }
+    println!("{}", report.summary());
+
+    for (path, contents) in [(&cli.json, report.to_json()), (&cli.junit, report.to_junit_xml())] {
+        if let Some(path) = path
+            && let Err(e) = std::fs::write(path, contents)
+        {
+            eprintln!("compat-check: cannot write {}: {}", path.display(), e);
+            return ExitCode::from(2);
+        }
+    }
+
+    if report.success() { ExitCode::SUCCESS } else { ExitCode::from(1) }
+}
@@ -0,0 +1,380 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Declarative contract test cases
+//!
+//! A [`ContractCase`] is a sequence of HTTP requests against a live gateway, each
+//! with the status it must answer with and assertions on the JSON body. Cases are
+//! built in Rust with the builder methods here or loaded from YAML or JSON files
+//! with the same shape:
+//!
+//! ```yaml
+//! - id: documents_round_trip
+//!   description: A created document reads back unchanged
+//!   requires: [document_management]
+//!   mutating: true
+//!   vars:
+//!     collection: compat_{run_id}
+//!   steps:
+//!     - method: POST
+//!       path: /api/v1/collections/{collection}/documents
+//!       body: {content: {name: Ada}}
+//!       status: 201
+//!       capture: {id: /id}
+//!     - method: GET
+//!       path: /api/v1/collections/{collection}/documents/{id}
+//!       status: 200
+//!       assertions:
+//!         - {kind: equals, pointer: /content/name, value: Ada}
+//!   cleanup:
+//!     - {method: DELETE, path: "/api/v1/collections/{collection}", status: 204}
+//! ```
+//!
+//! `{name}` in paths, header values and string values of bodies is replaced by a
+//! variable: one the case declares in `vars`, one captured from an earlier response
+//! with a JSON pointer, one given to the runner, or `run_id`, which is unique to
+//! each run so cases can name what they create without colliding with existing
+//! data. Case variables may refer to runner variables and `run_id`.
+
+use super::CompatibilityTestError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Capability tags of the built-in cases
+///
+/// Where the gateway reports a capability, the tag is the feature name it lists at
+/// `GET /api/v1/version`, so the runner can discover what a deployment offers.
+pub mod capability {
+    pub const AUTH: &str = "jwt_authentication";
+    pub const COLLECTIONS: &str = "database_collections";
+    pub const DOCUMENTS: &str = "document_management";
+    pub const TRANSACTIONS: &str = "document_transactions";
+    pub const SNAPSHOTS: &str = "read_snapshots";
+    pub const DOT_DEPLOYMENT: &str = "vm_dot_deployment";
+    pub const DOT_EXECUTION: &str = "vm_dot_execution";
+    /// Never reported by the gateway: the runtime's ABI registry is enabled and the
+    /// `abi_dot` variable names a deployed dot whose ABI declares a required input
+    pub const ABI_REGISTRY: &str = "abi_registry";
+}
+
+/// A named sequence of requests exercising one behavior of the gateway
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractCase {
+    pub id: String,
+    pub description: String,
+    /// Capabilities the target must offer for the case to run
+    #[serde(default)]
+    pub requires: Vec<String>,
+    /// Whether the case writes to the target; restricted runs skip such cases
+    #[serde(default)]
+    pub mutating: bool,
+    /// Variables available to the case's steps
+    #[serde(default)]
+    pub vars: BTreeMap<String, String>,
+    pub steps: Vec<ContractStep>,
+    /// Steps run after the case whether or not it passed, to remove what it created
+    #[serde(default)]
+    pub cleanup: Vec<ContractStep>,
+}
+
+impl ContractCase {
+    /// Create a case without steps
+    pub fn new(id: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            description: description.into(),
+            requires: Vec::new(),
+            mutating: false,
+            vars: BTreeMap::new(),
+            steps: Vec::new(),
+            cleanup: Vec::new(),
+        }
+    }
+
+    /// Only run the case against targets offering `capability`
+    pub fn requires(mut self, capability: impl Into<String>) -> Self {
+        self.requires.push(capability.into());
+        self
+    }
+
+    /// Mark the case as writing to the target
+    pub fn mutating(mut self) -> Self {
+        self.mutating = true;
+        self
+    }
+
+    /// Declare a variable; its value may itself refer to other variables
+    pub fn var(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.vars.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn step(mut self, step: ContractStep) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    pub fn cleanup(mut self, step: ContractStep) -> Self {
+        self.cleanup.push(step);
+        self
+    }
+}
+
+/// One request and what its response must look like
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractStep {
+    pub method: String,
+    /// Path and query, e.g. `/api/v1/collections/{collection}/documents?page=2`
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+    /// Extra request headers, e.g. `X-Transaction-Id`; values are substituted like paths
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    /// Whether to send the runner's bearer token
+    #[serde(default = "default_true")]
+    pub auth: bool,
+    /// Status the response must have
+    pub status: u16,
+    /// Whether to check the body against the schema the gateway's OpenAPI document
+    /// gives for the route and status. Error statuses are always checked for the
+    /// problem details shape.
+    #[serde(default = "default_true")]
+    pub check_schema: bool,
+    #[serde(default)]
+    pub assertions: Vec<JsonAssertion>,
+    /// Variables to set from the response body, by JSON pointer
+    #[serde(default)]
+    pub capture: BTreeMap<String, String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl ContractStep {
+    /// A request expected to succeed with 200 OK
+    pub fn new(method: impl Into<String>, path: impl Into<String>) -> Self {
+        Self {
+            method: method.into(),
+            path: path.into(),
+            body: None,
+            headers: BTreeMap::new(),
+            auth: true,
+            status: 200,
+            check_schema: true,
+            assertions: Vec::new(),
+            capture: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(path: impl Into<String>) -> Self {
+        Self::new("GET", path)
+    }
+
+    pub fn post(path: impl Into<String>) -> Self {
+        Self::new("POST", path)
+    }
+
+    pub fn put(path: impl Into<String>) -> Self {
+        Self::new("PUT", path)
+    }
+
+    pub fn delete(path: impl Into<String>) -> Self {
+        Self::new("DELETE", path)
+    }
+
+    pub fn body(mut self, body: Value) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    /// Send the request without a bearer token
+    pub fn anonymous(mut self) -> Self {
+        self.auth = false;
+        self
+    }
+
+    pub fn status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Skip the schema check, for bodies the OpenAPI document does not describe
+    pub fn unchecked(mut self) -> Self {
+        self.check_schema = false;
+        self
+    }
+
+    /// The value at `pointer` must equal `value`
+    pub fn expect_eq(mut self, pointer: impl Into<String>, value: Value) -> Self {
+        self.assertions.push(JsonAssertion::Equals { pointer: pointer.into(), value });
+        self
+    }
+
+    /// The array or object at `pointer` must have `length` elements
+    pub fn expect_len(mut self, pointer: impl Into<String>, length: usize) -> Self {
+        self.assertions.push(JsonAssertion::Length { pointer: pointer.into(), length });
+        self
+    }
+
+    /// Some value must exist at `pointer`
+    pub fn expect_present(mut self, pointer: impl Into<String>) -> Self {
+        self.assertions.push(JsonAssertion::Present { pointer: pointer.into() });
+        self
+    }
+
+    /// Set variable `name` to the value at `pointer`
+    pub fn capture(mut self, name: impl Into<String>, pointer: impl Into<String>) -> Self {
+        self.capture.insert(name.into(), pointer.into());
+        self
+    }
+}
+
+/// A check on the JSON body of a response
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JsonAssertion {
+    Equals { pointer: String, value: Value },
+    Length { pointer: String, length: usize },
+    Present { pointer: String },
+}
+
+impl JsonAssertion {
+    /// Why the body fails the assertion, if it does
+    pub fn check(&self, body: &Value) -> Option<String> {
+        match self {
+            JsonAssertion::Equals { pointer, value } => match body.pointer(pointer) {
+                Some(actual) if actual == value => None,
+                Some(actual) => Some(format!("{pointer} is {actual}, expected {value}")),
+                None => Some(format!("{pointer} is missing, expected {value}")),
+            },
+            JsonAssertion::Length { pointer, length } => {
+                let actual = match body.pointer(pointer) {
+                    Some(Value::Array(items)) => items.len(),
+                    Some(Value::Object(members)) => members.len(),
+                    Some(other) => return Some(format!("{pointer} is {other}, expected {length} elements")),
+                    None => return Some(format!("{pointer} is missing, expected {length} elements")),
+                };
+                (actual != *length).then(|| format!("{pointer} has {actual} elements, expected {length}"))
+            }
+            JsonAssertion::Present { pointer } => body.pointer(pointer).is_none().then(|| format!("{pointer} is missing")),
+        }
+    }
+}
+
+/// Load cases from a YAML or JSON file holding a list of them
+pub fn load_cases(path: &Path) -> Result<Vec<ContractCase>, CompatibilityTestError> {
+    let text = std::fs::read_to_string(path).map_err(|e| CompatibilityTestError::SetupFailed(format!("{}: {}", path.display(), e)))?;
+    let is_json = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+    let cases = if is_json {
+        serde_json::from_str(&text).map_err(|e| e.to_string())
+    } else {
+        serde_yaml::from_str(&text).map_err(|e| e.to_string())
+    };
+    cases.map_err(|e| CompatibilityTestError::SetupFailed(format!("{}: {}", path.display(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_yaml_cases_match_builders() {
+        let yaml = r#"
+- id: documents_round_trip
+  description: A created document reads back unchanged
+  requires: [document_management]
+  mutating: true
+  vars:
+    collection: compat_{run_id}
+  steps:
+    - method: POST
+      path: /api/v1/collections/{collection}/documents
+      body: {content: {name: Ada}}
+      status: 201
+      capture: {id: /id}
+    - method: GET
+      path: /api/v1/collections/{collection}/documents/{id}
+      status: 200
+      assertions:
+        - {kind: equals, pointer: /content/name, value: Ada}
+  cleanup:
+    - {method: DELETE, path: "/api/v1/collections/{collection}", status: 204}
+"#;
+        let loaded: Vec<ContractCase> = serde_yaml::from_str(yaml).unwrap();
+        let built = ContractCase::new("documents_round_trip", "A created document reads back unchanged")
+            .requires(capability::DOCUMENTS)
+            .mutating()
+            .var("collection", "compat_{run_id}")
+            .step(
+                ContractStep::post("/api/v1/collections/{collection}/documents")
+                    .body(json!({"content": {"name": "Ada"}}))
+                    .status(201)
+                    .capture("id", "/id"),
+            )
+            .step(ContractStep::get("/api/v1/collections/{collection}/documents/{id}").expect_eq("/content/name", json!("Ada")))
+            .cleanup(ContractStep::delete("/api/v1/collections/{collection}").status(204));
+        assert_eq!(loaded, vec![built]);
+    }
+
+    #[test]
+    fn test_json_assertions() {
+        let body = json!({"documents": [1, 2], "pagination": {"has_next": true}});
+        assert_eq!(
+            JsonAssertion::Length {
+                pointer: "/documents".into(),
+                length: 2
+            }
+            .check(&body),
+            None
+        );
+        assert_eq!(
+            JsonAssertion::Equals {
+                pointer: "/pagination/has_next".into(),
+                value: json!(true)
+            }
+            .check(&body),
+            None
+        );
+        assert!(
+            JsonAssertion::Equals {
+                pointer: "/pagination/has_next".into(),
+                value: json!(false)
+            }
+            .check(&body)
+            .unwrap()
+            .contains("expected false")
+        );
+        assert!(JsonAssertion::Present { pointer: "/missing".into() }.check(&body).is_some());
+        assert!(
+            JsonAssertion::Length {
+                pointer: "/pagination/has_next".into(),
+                length: 1
+            }
+            .check(&body)
+            .is_some()
+        );
+    }
+}
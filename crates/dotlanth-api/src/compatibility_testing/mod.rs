@@ -2,6 +2,19 @@
 // Copyright (C) 2025 Synerthink
 
 //! Compatibility testing automation for API versioning
+//!
+//! Besides the versioning checks in this module, [`contract`] cases exercise a
+//! live gateway's REST endpoints over HTTP: [`runner::ContractRunner`] runs them,
+//! checking statuses, bodies against the gateway's OpenAPI document and error
+//! shapes, and [`report::ContractReport`] renders the results as JSON or JUnit
+//! XML. [`suite::builtin_cases`] covers every registered route; the
+//! `compat-check` binary and `POST /api/v1/admin/compat-check` run it.
+
+pub mod contract;
+pub mod report;
+pub mod runner;
+pub mod schema;
+pub mod suite;
 
 use crate::versioning::{ApiVersion, CompatibilityChecker, DeprecationManager, ProtocolType, SchemaEvolutionManager, ServiceType, VersionRegistry};
 use serde::{Deserialize, Serialize};
@@ -0,0 +1,200 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Reports of contract runs, as JSON or JUnit XML
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+/// How a case ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaseStatus {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+/// Result of one contract case
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaseReport {
+    pub id: String,
+    pub description: String,
+    pub status: CaseStatus,
+    pub duration_ms: u64,
+    /// What went wrong, one entry per failed expectation
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failures: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skip_reason: Option<String>,
+}
+
+/// Result of a contract run against one target
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContractReport {
+    pub target: String,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    /// Capabilities the cases were selected by
+    pub capabilities: Vec<String>,
+    /// Whether mutating cases were skipped
+    pub restricted: bool,
+    pub cases: Vec<CaseReport>,
+}
+
+impl ContractReport {
+    fn count(&self, status: CaseStatus) -> usize {
+        self.cases.iter().filter(|case| case.status == status).count()
+    }
+
+    pub fn passed(&self) -> usize {
+        self.count(CaseStatus::Passed)
+    }
+
+    pub fn failed(&self) -> usize {
+        self.count(CaseStatus::Failed)
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.count(CaseStatus::Skipped)
+    }
+
+    /// Whether no case failed
+    pub fn success(&self) -> bool {
+        self.failed() == 0
+    }
+
+    /// One-line summary
+    pub fn summary(&self) -> String {
+        format!("{}: {} passed, {} failed, {} skipped", self.target, self.passed(), self.failed(), self.skipped())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    /// The report as a JUnit XML document, one test case per contract case
+    pub fn to_junit_xml(&self) -> String {
+        let seconds = |ms: u64| format!("{:.3}", ms as f64 / 1000.0);
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            xml,
+            "<testsuites name=\"compat-check\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{}\">",
+            self.cases.len(),
+            self.failed(),
+            self.skipped(),
+            seconds(self.duration_ms)
+        );
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{}\" timestamp=\"{}\">",
+            escape(&self.target),
+            self.cases.len(),
+            self.failed(),
+            self.skipped(),
+            seconds(self.duration_ms),
+            self.started_at.format("%Y-%m-%dT%H:%M:%S")
+        );
+        let _ = writeln!(xml, "    <properties>");
+        let _ = writeln!(xml, "      <property name=\"capabilities\" value=\"{}\"/>", escape(&self.capabilities.join(",")));
+        let _ = writeln!(xml, "      <property name=\"restricted\" value=\"{}\"/>", self.restricted);
+        let _ = writeln!(xml, "    </properties>");
+        for case in &self.cases {
+            let open = format!("    <testcase classname=\"contract\" name=\"{}\" time=\"{}\"", escape(&case.id), seconds(case.duration_ms));
+            match case.status {
+                CaseStatus::Passed => {
+                    let _ = writeln!(xml, "{open}/>");
+                }
+                CaseStatus::Skipped => {
+                    let _ = writeln!(xml, "{open}>");
+                    let _ = writeln!(xml, "      <skipped message=\"{}\"/>", escape(case.skip_reason.as_deref().unwrap_or("")));
+                    let _ = writeln!(xml, "    </testcase>");
+                }
+                CaseStatus::Failed => {
+                    let _ = writeln!(xml, "{open}>");
+                    let message = case.failures.first().map(String::as_str).unwrap_or("failed");
+                    let _ = writeln!(xml, "      <failure message=\"{}\">{}</failure>", escape(message), escape(&case.failures.join("\n")));
+                    let _ = writeln!(xml, "    </testcase>");
+                }
+            }
+        }
+        let _ = writeln!(xml, "  </testsuite>");
+        xml.push_str("</testsuites>\n");
+        xml
+    }
+}
+
+/// Escape text for XML attributes and content
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters other than whitespace are not allowed in XML 1.0
+            c if c.is_control() && !matches!(c, '\n' | '\r' | '\t') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn case(id: &str, status: CaseStatus) -> CaseReport {
+        CaseReport {
+            id: id.to_string(),
+            description: String::new(),
+            status,
+            duration_ms: 1500,
+            failures: if status == CaseStatus::Failed {
+                vec!["GET /api/v1/health: status 503, expected 200 <down>".to_string()]
+            } else {
+                Vec::new()
+            },
+            skip_reason: (status == CaseStatus::Skipped).then(|| "requires abi_registry".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_junit_report() {
+        let report = ContractReport {
+            target: "http://localhost:8080".to_string(),
+            started_at: Utc::now(),
+            duration_ms: 4500,
+            capabilities: vec!["jwt_authentication".to_string()],
+            restricted: false,
+            cases: vec![case("health", CaseStatus::Failed), case("login", CaseStatus::Passed), case("abi", CaseStatus::Skipped)],
+        };
+        assert_eq!((report.passed(), report.failed(), report.skipped(), report.success()), (1, 1, 1, false));
+
+        let xml = report.to_junit_xml();
+        assert!(xml.contains("<testsuites name=\"compat-check\" tests=\"3\" failures=\"1\" skipped=\"1\" time=\"4.500\">"), "{xml}");
+        assert!(xml.contains("<testcase classname=\"contract\" name=\"login\" time=\"1.500\"/>"), "{xml}");
+        assert!(xml.contains("<failure message=\"GET /api/v1/health: status 503, expected 200 &lt;down&gt;\">"), "{xml}");
+        assert!(xml.contains("<skipped message=\"requires abi_registry\"/>"), "{xml}");
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["cases"][0]["status"], "failed");
+        assert!(json["cases"][1].get("failures").is_none());
+    }
+}
@@ -0,0 +1,367 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Runs contract cases against a live gateway over HTTP/1.1
+
+use super::CompatibilityTestError;
+use super::contract::{ContractCase, ContractStep};
+use super::report::{CaseReport, CaseStatus, ContractReport};
+use super::schema;
+use crate::openapi;
+use chrono::Utc;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::{Method, Request};
+use hyper_util::rt::TokioIo;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+/// Time each request may take when the configuration does not say
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What to run cases against and how
+#[derive(Debug, Clone)]
+pub struct RunnerConfig {
+    /// Base URL of the gateway, e.g. `http://localhost:8080`; a path is prefixed to every request
+    pub target: String,
+    /// Bearer token sent with steps that authenticate
+    pub token: Option<String>,
+    /// Capabilities of the target; `None` asks the target at `GET /api/v1/version`
+    pub capabilities: Option<Vec<String>>,
+    /// Capabilities to treat as offered even if the target does not report them, e.g.
+    /// [`capability::ABI_REGISTRY`](super::contract::capability::ABI_REGISTRY)
+    pub with: Vec<String>,
+    /// Capabilities to treat as missing even if the target offers them
+    pub without: Vec<String>,
+    /// Skip mutating cases, for targets whose data must not change
+    pub restricted: bool,
+    /// Variables available to every case
+    pub vars: BTreeMap<String, String>,
+    pub request_timeout: Duration,
+}
+
+impl RunnerConfig {
+    pub fn new(target: impl Into<String>) -> Self {
+        Self {
+            target: target.into(),
+            token: None,
+            capabilities: None,
+            with: Vec::new(),
+            without: Vec::new(),
+            restricted: false,
+            vars: BTreeMap::new(),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+        }
+    }
+}
+
+/// Runs contract cases against one target
+pub struct ContractRunner {
+    config: RunnerConfig,
+    /// `host:port` to connect to
+    authority: String,
+    /// Path the target serves the API below, without a trailing slash
+    base_path: String,
+    spec: Value,
+}
+
+/// A response as the runner sees it
+struct HttpResponse {
+    status: u16,
+    content_type: Option<String>,
+    /// The JSON body, `null` when empty, or the text of a body that is not JSON
+    body: Value,
+    is_json: bool,
+}
+
+impl ContractRunner {
+    pub fn new(config: RunnerConfig) -> Result<Self, CompatibilityTestError> {
+        let url = url::Url::parse(&config.target).map_err(|e| CompatibilityTestError::SetupFailed(format!("invalid target {}: {}", config.target, e)))?;
+        if url.scheme() != "http" {
+            return Err(CompatibilityTestError::SetupFailed(format!("unsupported target scheme {}; only http is supported", url.scheme())));
+        }
+        let host = url.host_str().ok_or_else(|| CompatibilityTestError::SetupFailed(format!("target {} has no host", config.target)))?;
+        let authority = format!("{}:{}", host, url.port_or_known_default().unwrap_or(80));
+        let base_path = url.path().trim_end_matches('/').to_string();
+        let spec = serde_json::from_str(&openapi::generate_spec()).map_err(|e| CompatibilityTestError::SetupFailed(format!("invalid OpenAPI document: {}", e)))?;
+
+        Ok(Self { config, authority, base_path, spec })
+    }
+
+    /// Run `cases` in order and report how each went
+    pub async fn run(&self, cases: &[ContractCase]) -> ContractReport {
+        let started_at = Utc::now();
+        let started = Instant::now();
+        let mut capabilities = match &self.config.capabilities {
+            Some(capabilities) => capabilities.clone(),
+            None => self.discover_capabilities().await,
+        };
+        capabilities.extend(self.config.with.iter().cloned());
+        capabilities.retain(|capability| !self.config.without.contains(capability));
+        capabilities.sort();
+        capabilities.dedup();
+
+        let mut vars = self.config.vars.clone();
+        vars.entry("run_id".to_string()).or_insert_with(|| uuid::Uuid::new_v4().simple().to_string()[..8].to_string());
+
+        let mut reports = Vec::with_capacity(cases.len());
+        for case in cases {
+            reports.push(self.run_case(case, &capabilities, &vars).await);
+        }
+
+        ContractReport {
+            target: self.config.target.clone(),
+            started_at,
+            duration_ms: started.elapsed().as_millis() as u64,
+            capabilities,
+            restricted: self.config.restricted,
+            cases: reports,
+        }
+    }
+
+    /// Features the target lists at `GET /api/v1/version`; none if it cannot be asked
+    async fn discover_capabilities(&self) -> Vec<String> {
+        match self.send(&Method::GET, "/api/v1/version", &BTreeMap::new(), None, false).await {
+            Ok(response) if response.status == 200 => response.body["features"]
+                .as_array()
+                .map(|features| features.iter().filter_map(Value::as_str).map(str::to_string).collect())
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        }
+    }
+
+    async fn run_case(&self, case: &ContractCase, capabilities: &[String], runner_vars: &BTreeMap<String, String>) -> CaseReport {
+        let started = Instant::now();
+        let report = |status, failures, skip_reason| CaseReport {
+            id: case.id.clone(),
+            description: case.description.clone(),
+            status,
+            duration_ms: started.elapsed().as_millis() as u64,
+            failures,
+            skip_reason,
+        };
+
+        let missing: Vec<&str> = case.requires.iter().filter(|required| !capabilities.contains(required)).map(String::as_str).collect();
+        if !missing.is_empty() {
+            return report(CaseStatus::Skipped, Vec::new(), Some(format!("target lacks {}", missing.join(", "))));
+        }
+        if case.mutating && self.config.restricted {
+            return report(CaseStatus::Skipped, Vec::new(), Some("mutating case in a restricted run".to_string()));
+        }
+
+        let mut vars = runner_vars.clone();
+        for (name, value) in &case.vars {
+            match substitute(value, runner_vars) {
+                Ok(value) => vars.insert(name.clone(), value),
+                Err(e) => return report(CaseStatus::Failed, vec![format!("variable {}: {}", name, e)], None),
+            };
+        }
+
+        let mut failures = Vec::new();
+        for (index, step) in case.steps.iter().enumerate() {
+            failures = self.run_step(step, &mut vars).await.into_iter().map(|failure| format!("step {}: {}", index + 1, failure)).collect();
+            if !failures.is_empty() {
+                break;
+            }
+        }
+
+        // Cleanup is best effort; a case that failed part way may have nothing to remove
+        for step in &case.cleanup {
+            let _ = self.run_step(step, &mut vars).await;
+        }
+
+        let status = if failures.is_empty() { CaseStatus::Passed } else { CaseStatus::Failed };
+        report(status, failures, None)
+    }
+
+    /// Send one step's request and return every way the response misses its expectations
+    async fn run_step(&self, step: &ContractStep, vars: &mut BTreeMap<String, String>) -> Vec<String> {
+        let label = format!("{} {}", step.method, step.path);
+        let prepared = (|| {
+            let method = Method::from_bytes(step.method.to_ascii_uppercase().as_bytes()).map_err(|_| format!("invalid method {}", step.method))?;
+            let path = substitute(&step.path, vars)?;
+            let headers = step
+                .headers
+                .iter()
+                .map(|(name, value)| Ok((name.clone(), substitute(value, vars)?)))
+                .collect::<Result<BTreeMap<_, _>, String>>()?;
+            let body = step.body.as_ref().map(|body| substitute_value(body, vars)).transpose()?;
+            Ok::<_, String>((method, path, headers, body))
+        })();
+        let (method, path, headers, body) = match prepared {
+            Ok(prepared) => prepared,
+            Err(e) => return vec![format!("{}: {}", label, e)],
+        };
+        let label = format!("{} {}", method, path);
+
+        let response = match self.send(&method, &path, &headers, body.as_ref(), step.auth).await {
+            Ok(response) => response,
+            Err(e) => return vec![format!("{}: {}", label, e)],
+        };
+
+        let mut issues = Vec::new();
+        if response.status != step.status {
+            let detail = response.body.get("detail").and_then(Value::as_str).map(|detail| format!(" ({})", detail)).unwrap_or_default();
+            issues.push(format!("status {}, expected {}{}", response.status, step.status, detail));
+        } else if response.status >= 400 {
+            issues.extend(self.check_problem(&response));
+        } else if step.check_schema
+            && let Some(schema) = schema::response_schema(&self.spec, &method, &path, response.status)
+        {
+            if response.is_json {
+                issues.extend(schema::validate(&self.spec, schema, &response.body));
+            } else {
+                issues.push("body is not JSON".to_string());
+            }
+        }
+
+        if issues.is_empty() {
+            issues.extend(step.assertions.iter().filter_map(|assertion| assertion.check(&response.body)));
+            for (name, pointer) in &step.capture {
+                match response.body.pointer(pointer) {
+                    Some(Value::String(value)) => {
+                        vars.insert(name.clone(), value.clone());
+                    }
+                    Some(value) => {
+                        vars.insert(name.clone(), value.to_string());
+                    }
+                    None => issues.push(format!("{} is missing, cannot capture {}", pointer, name)),
+                }
+            }
+        }
+
+        issues.into_iter().map(|issue| format!("{}: {}", label, issue)).collect()
+    }
+
+    /// Ways an error response deviates from RFC 7807 problem details
+    fn check_problem(&self, response: &HttpResponse) -> Vec<String> {
+        let mut issues = Vec::new();
+        if !response.content_type.as_deref().is_some_and(|content_type| content_type.starts_with("application/problem+json")) {
+            issues.push(format!("content type {}, expected application/problem+json", response.content_type.as_deref().unwrap_or("missing")));
+        }
+        issues.extend(schema::validate(&self.spec, &json!({"$ref": "#/components/schemas/ProblemDetails"}), &response.body));
+        if let Some(status) = response.body.get("status").and_then(Value::as_u64)
+            && status != u64::from(response.status)
+        {
+            issues.push(format!("problem status {} differs from the response status {}", status, response.status));
+        }
+        issues
+    }
+
+    async fn send(&self, method: &Method, path: &str, headers: &BTreeMap<String, String>, body: Option<&Value>, auth: bool) -> Result<HttpResponse, String> {
+        let exchange = async {
+            let stream = TcpStream::connect(&self.authority).await.map_err(|e| format!("cannot connect to {}: {}", self.authority, e))?;
+            let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await.map_err(|e| e.to_string())?;
+            tokio::spawn(connection);
+
+            let mut request = Request::builder()
+                .method(method.clone())
+                .uri(format!("{}{}", self.base_path, path))
+                .header("host", &self.authority)
+                .header("accept", "application/json");
+            if body.is_some() {
+                request = request.header("content-type", "application/json");
+            }
+            if let (true, Some(token)) = (auth, self.config.token.as_ref()) {
+                request = request.header("authorization", format!("Bearer {}", token));
+            }
+            for (name, value) in headers {
+                request = request.header(name.as_str(), value.as_str());
+            }
+            let body = body.map(|body| Bytes::from(body.to_string())).unwrap_or_default();
+            let request = request.body(Full::new(body)).map_err(|e| e.to_string())?;
+
+            let response = sender.send_request(request).await.map_err(|e| e.to_string())?;
+            let status = response.status().as_u16();
+            let content_type = response.headers().get("content-type").and_then(|value| value.to_str().ok()).map(str::to_string);
+            let bytes = response.into_body().collect().await.map_err(|e| e.to_string())?.to_bytes();
+            let (body, is_json) = if bytes.is_empty() {
+                (Value::Null, true)
+            } else {
+                match serde_json::from_slice(&bytes) {
+                    Ok(body) => (body, true),
+                    Err(_) => (Value::String(String::from_utf8_lossy(&bytes).into_owned()), false),
+                }
+            };
+            Ok(HttpResponse { status, content_type, body, is_json })
+        };
+
+        timeout(self.config.request_timeout, exchange)
+            .await
+            .map_err(|_| format!("no response within {:?}", self.config.request_timeout))?
+    }
+}
+
+/// Replace `{name}` in `text` with variables; braces around anything but a name are kept
+fn substitute(text: &str, vars: &BTreeMap<String, String>) -> Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let name_len = after.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(after.len());
+        if name_len > 0 && after[name_len..].starts_with('}') {
+            let name = &after[..name_len];
+            result.push_str(vars.get(name).ok_or_else(|| format!("unknown variable {{{}}}", name))?);
+            rest = &after[name_len + 1..];
+        } else {
+            result.push('{');
+            rest = after;
+        }
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Substitute variables in every string of a JSON value
+fn substitute_value(value: &Value, vars: &BTreeMap<String, String>) -> Result<Value, String> {
+    Ok(match value {
+        Value::String(text) => Value::String(substitute(text, vars)?),
+        Value::Array(items) => Value::Array(items.iter().map(|item| substitute_value(item, vars)).collect::<Result<_, _>>()?),
+        Value::Object(members) => Value::Object(members.iter().map(|(key, member)| Ok((key.clone(), substitute_value(member, vars)?))).collect::<Result<_, String>>()?),
+        other => other.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_substitution() {
+        let vars = BTreeMap::from([("collection".to_string(), "compat_1".to_string()), ("id".to_string(), "42".to_string())]);
+        assert_eq!(
+            substitute("/api/v1/collections/{collection}/documents/{id}", &vars).unwrap(),
+            "/api/v1/collections/compat_1/documents/42"
+        );
+        assert_eq!(substitute("{not a var} {}", &vars).unwrap(), "{not a var} {}");
+        assert!(substitute("/{missing}", &vars).unwrap_err().contains("{missing}"));
+
+        let body = substitute_value(&json!({"name": "{collection}", "tags": ["{id}", 7]}), &vars).unwrap();
+        assert_eq!(body, json!({"name": "compat_1", "tags": ["42", 7]}));
+    }
+
+    #[test]
+    fn test_targets() {
+        let runner = ContractRunner::new(RunnerConfig::new("http://gateway.local/dotlanth/")).unwrap();
+        assert_eq!((runner.authority.as_str(), runner.base_path.as_str()), ("gateway.local:80", "/dotlanth"));
+        assert!(ContractRunner::new(RunnerConfig::new("https://gateway.local")).is_err());
+        assert!(ContractRunner::new(RunnerConfig::new("not a url")).is_err());
+    }
+}
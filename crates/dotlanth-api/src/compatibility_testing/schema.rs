@@ -0,0 +1,171 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Response bodies checked against the gateway's OpenAPI document
+//!
+//! The runner checks responses against the document this build of the gateway
+//! generates, not the one the target serves: the point is to catch a target that
+//! no longer answers the way this gateway's clients were promised. The checks
+//! cover the parts of OpenAPI 3.0 schemas the generated document uses: `$ref`,
+//! `type`, `nullable`, `enum`, `required`, `properties`, `additionalProperties`,
+//! `items`, `allOf`, `oneOf` and `anyOf`. Formats are not checked.
+
+use crate::openapi;
+use hyper::Method;
+use serde_json::Value;
+
+/// The schema the document gives for a JSON response body, if it gives one
+pub fn response_schema<'a>(spec: &'a Value, method: &Method, path: &str, status: u16) -> Option<&'a Value> {
+    let path = path.split_once('?').map_or(path, |(path, _)| path);
+    let (version, below_prefix) = openapi::split_version_prefix(path)?;
+    let route = openapi::find_versioned_route(method, version, below_prefix)?;
+    let operation = spec.get("paths")?.get(route.full_path())?.get(method.as_str().to_lowercase())?;
+    let content = operation.get("responses")?.get(status.to_string())?.get("content")?.as_object()?;
+    content.iter().find(|(media_type, _)| media_type.contains("json")).and_then(|(_, media)| media.get("schema"))
+}
+
+/// Every way `value` deviates from `schema`, located by JSON pointer
+pub fn validate(spec: &Value, schema: &Value, value: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    check(spec, schema, value, "", &mut errors);
+    errors
+}
+
+fn check(spec: &Value, schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    let at = if path.is_empty() { "body" } else { path };
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        match reference.strip_prefix('#').and_then(|pointer| spec.pointer(pointer)) {
+            Some(target) => check(spec, target, value, path, errors),
+            None => errors.push(format!("{at}: unresolved schema reference {reference}")),
+        }
+        return;
+    }
+    if value.is_null() && schema.get("nullable").and_then(Value::as_bool).unwrap_or(false) {
+        return;
+    }
+
+    for part in schema.get("allOf").and_then(Value::as_array).into_iter().flatten() {
+        check(spec, part, value, path, errors);
+    }
+    for keyword in ["oneOf", "anyOf"] {
+        if let Some(alternatives) = schema.get(keyword).and_then(Value::as_array) {
+            let matches = alternatives.iter().any(|alternative| {
+                let mut alternative_errors = Vec::new();
+                check(spec, alternative, value, path, &mut alternative_errors);
+                alternative_errors.is_empty()
+            });
+            if !matches {
+                errors.push(format!("{at}: matches none of the {keyword} alternatives"));
+            }
+        }
+    }
+
+    let expected = schema.get("type").and_then(Value::as_str).or_else(|| schema.get("properties").map(|_| "object"));
+    if let Some(expected) = expected {
+        let matches = match expected {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "integer" => value.is_i64() || value.is_u64(),
+            "number" => value.is_number(),
+            "boolean" => value.is_boolean(),
+            _ => true,
+        };
+        if !matches {
+            errors.push(format!("{at}: expected {expected}, found {}", kind(value)));
+            return;
+        }
+    }
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array)
+        && !allowed.contains(value)
+    {
+        errors.push(format!("{at}: {value} is not one of {}", Value::Array(allowed.clone())));
+    }
+
+    if let Some(members) = value.as_object() {
+        for name in schema.get("required").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str) {
+            if !members.contains_key(name) {
+                errors.push(format!("{at}: missing required member {name}"));
+            }
+        }
+        let properties = schema.get("properties").and_then(Value::as_object);
+        let additional = schema.get("additionalProperties").filter(|additional| additional.is_object());
+        for (name, member) in members {
+            let member_path = format!("{path}/{}", name.replace('~', "~0").replace('/', "~1"));
+            if let Some(member_schema) = properties.and_then(|properties| properties.get(name)).or(additional) {
+                check(spec, member_schema, member, &member_path, errors);
+            }
+        }
+    }
+    if let (Some(items), Some(elements)) = (schema.get("items"), value.as_array()) {
+        for (index, element) in elements.iter().enumerate() {
+            check(spec, items, element, &format!("{path}/{index}"), errors);
+        }
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn spec() -> Value {
+        serde_json::from_str(&openapi::generate_spec()).unwrap()
+    }
+
+    #[test]
+    fn test_documented_response_schemas_are_found() {
+        let spec = spec();
+        let list = response_schema(&spec, &Method::GET, "/api/v1/collections/users/documents?page=2", 200).unwrap();
+        assert_eq!(list["$ref"], "#/components/schemas/DocumentList");
+        let problem = response_schema(&spec, &Method::GET, "/api/v1/collections/users/documents", 404).unwrap();
+        assert_eq!(problem["$ref"], "#/components/schemas/ProblemDetails");
+        assert!(response_schema(&spec, &Method::DELETE, "/api/v1/collections/users", 204).is_none());
+        assert!(response_schema(&spec, &Method::GET, "/api/v1/unknown", 200).is_none());
+    }
+
+    #[test]
+    fn test_bodies_are_checked_against_components() {
+        let spec = spec();
+        let schema = json!({"$ref": "#/components/schemas/PaginationInfo"});
+        let valid = json!({"page": 1, "page_size": 20, "total_items": 3, "total_pages": 1, "has_next": false, "has_previous": false});
+        assert_eq!(validate(&spec, &schema, &valid), Vec::<String>::new());
+
+        let mut invalid = valid.clone();
+        invalid["has_next"] = json!("no");
+        invalid.as_object_mut().unwrap().remove("page");
+        let errors = validate(&spec, &schema, &invalid);
+        assert!(errors.contains(&"body: missing required member page".to_string()), "{errors:?}");
+        assert!(errors.contains(&"/has_next: expected boolean, found string".to_string()), "{errors:?}");
+
+        let array = json!({"type": "array", "items": {"type": "integer"}});
+        assert_eq!(validate(&spec, &array, &json!([1, "two"])), ["/1: expected integer, found string"]);
+        let nullable = json!({"type": "string", "nullable": true});
+        assert!(validate(&spec, &nullable, &Value::Null).is_empty());
+    }
+}
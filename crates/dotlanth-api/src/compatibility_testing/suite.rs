@@ -0,0 +1,273 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Built-in contract cases covering every REST route the gateway serves
+//!
+//! Cases that create data name it after the run's `run_id` and remove it in their
+//! cleanup steps. The suite expects a token whose user exists on the target and
+//! holds the document, deployment and execution permissions; the gateway's default
+//! `admin` user does.
+
+use super::contract::{ContractCase, ContractStep, capability};
+use crate::transactions::TRANSACTION_HEADER;
+use serde_json::json;
+
+/// The built-in cases, in the order they are run
+pub fn builtin_cases() -> Vec<ContractCase> {
+    vec![
+        ContractCase::new("health", "Health reports every backend healthy")
+            .requires(capability::COLLECTIONS)
+            .requires(capability::DOT_EXECUTION)
+            .step(ContractStep::get("/api/v1/health").anonymous().expect_eq("/status", json!("healthy"))),
+        ContractCase::new("readiness", "A running gateway is ready for traffic").step(ContractStep::get("/api/v1/ready").anonymous().expect_eq("/status", json!("ready"))),
+        ContractCase::new("liveness", "A running gateway is alive").step(ContractStep::get("/livez").anonymous().expect_eq("/status", json!("alive"))),
+        ContractCase::new("version", "Version info lists the gateway's features").step(ContractStep::get("/api/v1/version").anonymous().expect_present("/features")),
+        ContractCase::new("login_rejects_bad_credentials", "Logging in with a wrong password is refused")
+            .requires(capability::AUTH)
+            .step(
+                ContractStep::post("/api/v1/auth/login")
+                    .anonymous()
+                    .body(json!({"username": "compat-check-{run_id}", "password": "wrong"}))
+                    .status(401),
+            ),
+        ContractCase::new("profile_requires_token", "The profile is only served to authenticated callers")
+            .requires(capability::AUTH)
+            .step(ContractStep::get("/api/v1/auth/profile").anonymous().status(401))
+            .step(ContractStep::get("/api/v1/auth/profile").expect_present("/id")),
+        ContractCase::new("collections_lifecycle", "Collections are created once, listed and deleted")
+            .requires(capability::COLLECTIONS)
+            .mutating()
+            .var("collection", "compat_{run_id}_lifecycle")
+            .step(ContractStep::post("/api/v1/collections/{collection}").status(201).expect_eq("/document_count", json!(0)))
+            .step(ContractStep::post("/api/v1/collections/{collection}").status(409))
+            .step(ContractStep::get("/api/v1/collections"))
+            .step(ContractStep::delete("/api/v1/collections/{collection}").status(204))
+            .step(ContractStep::delete("/api/v1/collections/{collection}").status(404))
+            .cleanup(ContractStep::delete("/api/v1/collections/{collection}").status(204)),
+        ContractCase::new("documents_round_trip", "A document reads back as written, updated and deleted")
+            .requires(capability::DOCUMENTS)
+            .mutating()
+            .var("collection", "compat_{run_id}_documents")
+            .step(ContractStep::post("/api/v1/collections/{collection}").status(201))
+            .step(
+                ContractStep::post("/api/v1/collections/{collection}/documents")
+                    .body(json!({"content": {"name": "Ada", "city": "London"}}))
+                    .status(201)
+                    .capture("id", "/id"),
+            )
+            .step(ContractStep::get("/api/v1/collections/{collection}/documents/{id}").expect_eq("/content/name", json!("Ada")))
+            .step(
+                ContractStep::put("/api/v1/collections/{collection}/documents/{id}")
+                    .body(json!({"content": {"name": "Ada Lovelace", "city": "London"}}))
+                    .expect_eq("/content/name", json!("Ada Lovelace")),
+            )
+            .step(ContractStep::delete("/api/v1/collections/{collection}/documents/{id}").status(204))
+            .step(ContractStep::get("/api/v1/collections/{collection}/documents/{id}").status(404))
+            .cleanup(ContractStep::delete("/api/v1/collections/{collection}").status(204)),
+        ContractCase::new("documents_paginate", "Document listings page with accurate pagination info")
+            .requires(capability::DOCUMENTS)
+            .mutating()
+            .var("collection", "compat_{run_id}_pages")
+            .step(ContractStep::post("/api/v1/collections/{collection}").status(201))
+            .step(ContractStep::post("/api/v1/collections/{collection}/documents").body(json!({"content": {"n": 1}})).status(201))
+            .step(ContractStep::post("/api/v1/collections/{collection}/documents").body(json!({"content": {"n": 2}})).status(201))
+            .step(ContractStep::post("/api/v1/collections/{collection}/documents").body(json!({"content": {"n": 3}})).status(201))
+            .step(
+                ContractStep::get("/api/v1/collections/{collection}/documents?page=1&page_size=2")
+                    .expect_len("/documents", 2)
+                    .expect_eq("/pagination/total_items", json!(3))
+                    .expect_eq("/pagination/total_pages", json!(2))
+                    .expect_eq("/pagination/has_next", json!(true))
+                    .expect_eq("/pagination/has_previous", json!(false)),
+            )
+            .step(
+                ContractStep::get("/api/v1/collections/{collection}/documents?page=2&page_size=2")
+                    .expect_len("/documents", 1)
+                    .expect_eq("/pagination/has_next", json!(false))
+                    .expect_eq("/pagination/has_previous", json!(true)),
+            )
+            .step(ContractStep::get("/api/v1/collections/{collection}/documents?page_size=0").status(400))
+            .step(ContractStep::get("/api/v1/collections/{collection}/documents?page_size=101").status(400))
+            .cleanup(ContractStep::delete("/api/v1/collections/{collection}").status(204)),
+        ContractCase::new("documents_search_and_aggregate", "Search and aggregation see stored documents and reject malformed requests")
+            .requires(capability::DOCUMENTS)
+            .mutating()
+            .var("collection", "compat_{run_id}_query")
+            .step(ContractStep::post("/api/v1/collections/{collection}").status(201))
+            .step(
+                ContractStep::post("/api/v1/collections/{collection}/documents")
+                    .body(json!({"content": {"name": "Ada", "city": "London"}}))
+                    .status(201),
+            )
+            .step(
+                ContractStep::post("/api/v1/collections/{collection}/documents")
+                    .body(json!({"content": {"name": "Grace", "city": "London"}}))
+                    .status(201),
+            )
+            .step(
+                ContractStep::post("/api/v1/collections/{collection}/documents")
+                    .body(json!({"content": {"name": "Linus", "city": "Lisbon"}}))
+                    .status(201),
+            )
+            .step(ContractStep::get("/api/v1/collections/{collection}/search?q=grace").expect_eq("/total_matches", json!(1)))
+            .step(ContractStep::get("/api/v1/collections/{collection}/search").status(400))
+            .step(
+                ContractStep::post("/api/v1/collections/{collection}/aggregate")
+                    .body(json!({"group_by": ["city"], "aggregates": [{"function": "count"}]}))
+                    .expect_len("/groups", 2)
+                    .expect_eq("/groups/1/values/count", json!(2)),
+            )
+            .step(
+                ContractStep::post("/api/v1/collections/{collection}/aggregate")
+                    .body(json!({"aggregates": [{"function": "median"}]}))
+                    .status(400),
+            )
+            .cleanup(ContractStep::delete("/api/v1/collections/{collection}").status(204)),
+        ContractCase::new("documents_missing_collection", "Reads from a collection that does not exist are not found")
+            .requires(capability::DOCUMENTS)
+            .var("collection", "compat_{run_id}_missing")
+            .step(ContractStep::get("/api/v1/collections/{collection}/documents").status(404))
+            .step(ContractStep::get("/api/v1/collections/{collection}/search?q=x").status(404))
+            .step(
+                ContractStep::post("/api/v1/collections/{collection}/aggregate")
+                    .body(json!({"aggregates": [{"function": "count"}]}))
+                    .status(404),
+            ),
+        ContractCase::new("transactions_commit", "Writes in a transaction are invisible until it commits")
+            .requires(capability::TRANSACTIONS)
+            .mutating()
+            .var("collection", "compat_{run_id}_transactions")
+            .step(ContractStep::post("/api/v1/collections/{collection}").status(201))
+            .step(
+                ContractStep::post("/api/v1/transactions")
+                    .body(json!({"isolation": "read_committed"}))
+                    .status(201)
+                    .capture("txn", "/id"),
+            )
+            .step(
+                ContractStep::post("/api/v1/collections/{collection}/documents")
+                    .header(TRANSACTION_HEADER, "{txn}")
+                    .body(json!({"content": {"balance": 10}}))
+                    .status(201)
+                    .capture("id", "/id"),
+            )
+            .step(ContractStep::get("/api/v1/collections/{collection}/documents/{id}").status(404))
+            .step(ContractStep::post("/api/v1/transactions/{txn}/commit").status(204))
+            .step(ContractStep::get("/api/v1/collections/{collection}/documents/{id}").expect_eq("/content/balance", json!(10)))
+            .step(ContractStep::post("/api/v1/transactions/{txn}/commit").status(404))
+            .cleanup(ContractStep::delete("/api/v1/collections/{collection}").status(204)),
+        ContractCase::new("transactions_abort", "An aborted transaction cannot be finished again")
+            .requires(capability::TRANSACTIONS)
+            .mutating()
+            .step(ContractStep::post("/api/v1/transactions").status(201).capture("txn", "/id"))
+            .step(ContractStep::delete("/api/v1/transactions/{txn}").status(204))
+            .step(ContractStep::delete("/api/v1/transactions/{txn}").status(404)),
+        ContractCase::new("read_snapshot", "Reads through a snapshot do not see writes made after it was taken")
+            .requires(capability::SNAPSHOTS)
+            .mutating()
+            .var("collection", "compat_{run_id}_snapshot")
+            .step(ContractStep::post("/api/v1/collections/{collection}").status(201))
+            .step(ContractStep::post("/api/v1/snapshots").status(201).capture("snapshot", "/token"))
+            .step(ContractStep::post("/api/v1/collections/{collection}/documents").body(json!({"content": {"balance": 10}})).status(201))
+            .step(ContractStep::get("/api/v1/collections/{collection}/documents?snapshot={snapshot}").expect_len("/documents", 0))
+            .step(ContractStep::get("/api/v1/collections/{collection}/documents").expect_len("/documents", 1))
+            .step(ContractStep::delete("/api/v1/snapshots/{snapshot}").status(204))
+            .step(ContractStep::get("/api/v1/collections/{collection}/documents?snapshot={snapshot}").status(404))
+            .cleanup(ContractStep::delete("/api/v1/collections/{collection}").status(204)),
+        ContractCase::new("vm_overview", "The VM reports its status, architectures and dots")
+            .requires(capability::DOT_EXECUTION)
+            .step(ContractStep::get("/api/v1/vm/status"))
+            .step(ContractStep::get("/api/v1/vm/architectures"))
+            .step(ContractStep::get("/api/v1/vm/dots")),
+        ContractCase::new("vm_unknown_dot", "Requests for a dot that does not exist are not found")
+            .requires(capability::DOT_EXECUTION)
+            .var("dot", "compat-{run_id}-missing")
+            .step(ContractStep::get("/api/v1/vm/dots/{dot}/state").status(404))
+            .step(ContractStep::post("/api/v1/vm/dots/{dot}/execute").body(json!({"function": "main", "arguments": []})).status(404))
+            .step(ContractStep::delete("/api/v1/vm/dots/{dot}").status(404)),
+        ContractCase::new("vm_request_validation", "Malformed execution and state requests are rejected before reaching the VM")
+            .requires(capability::DOT_EXECUTION)
+            .var("dot", "compat-{run_id}-missing")
+            .step(ContractStep::post("/api/v1/vm/dots/{dot}/execute").body(json!({"function": "", "arguments": []})).status(400))
+            .step(ContractStep::get("/api/v1/vm/dots/{dot}/state/diff?to=2").status(400)),
+        ContractCase::new("deploy_validation", "Deployments without a name or bytecode are rejected")
+            .requires(capability::DOT_DEPLOYMENT)
+            .step(ContractStep::post("/api/v1/vm/dots/deploy").body(json!({"name": "", "bytecode": "AA=="})).status(400))
+            .step(ContractStep::post("/api/v1/vm/dots/deploy").body(json!({"name": "compat-{run_id}", "bytecode": ""})).status(400))
+            .step(ContractStep::get("/api/v1/vm/deployments/compat-{run_id}-missing").status(404)),
+        ContractCase::new("abi_input_validation", "Execution inputs missing from a dot's ABI are reported per field")
+            .requires(capability::DOT_EXECUTION)
+            .requires(capability::ABI_REGISTRY)
+            .step(
+                ContractStep::post("/api/v1/vm/dots/{abi_dot}/execute")
+                    .body(json!({"function": "main", "arguments": []}))
+                    .status(400)
+                    .expect_present("/errors/0"),
+            ),
+        ContractCase::new("gateway_endpoints", "The gateway reports its health, metrics, cache and admission counters")
+            .step(ContractStep::get("/api/v1/gateway/health").expect_eq("/status", json!("healthy")))
+            .step(ContractStep::get("/api/v1/gateway/metrics").expect_present("/metrics"))
+            .step(ContractStep::get("/api/v1/gateway/cache"))
+            .step(ContractStep::get("/api/v1/gateway/admission").expect_present("/counters/shed")),
+        ContractCase::new("gateway_config", "Runtime settings are readable and invalid changes are refused")
+            .step(ContractStep::get("/api/v1/gateway/config").expect_present("/admission/max_in_flight"))
+            .step(ContractStep::put("/api/v1/gateway/config").body(json!({"admission": {"max_in_flight": 0}})).status(400)),
+        ContractCase::new("anonymous_requests_are_rejected", "Protected routes refuse requests without a token")
+            .step(ContractStep::get("/api/v1/collections").anonymous().status(401))
+            .step(ContractStep::post("/api/v1/transactions").anonymous().status(401))
+            .step(ContractStep::get("/api/v1/vm/dots/compat/events").anonymous().status(401))
+            .step(ContractStep::get("/api/v1/gateway/rate-limits").anonymous().status(401))
+            .step(ContractStep::get("/api/v1/admin/audit").anonymous().status(401))
+            .step(ContractStep::post("/api/v1/admin/compat-check").anonymous().status(401))
+            .step(ContractStep::get("/api/v1/admin/api-keys").anonymous().status(401))
+            .step(ContractStep::post("/api/v1/admin/api-keys").anonymous().status(401))
+            .step(ContractStep::post("/api/v1/admin/api-keys/compat/rotate").anonymous().status(401))
+            .step(ContractStep::delete("/api/v1/admin/api-keys/compat").anonymous().status(401)),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::openapi::ROUTES;
+    use hyper::Method;
+
+    #[test]
+    fn test_every_route_is_exercised() {
+        let cases = builtin_cases();
+        let steps: Vec<(Method, &str)> = cases
+            .iter()
+            .flat_map(|case| &case.steps)
+            .map(|step| (Method::from_bytes(step.method.as_bytes()).unwrap(), step.path.split('?').next().unwrap()))
+            .collect();
+
+        let unexercised: Vec<String> = ROUTES
+            .iter()
+            .filter(|route| !steps.iter().any(|(method, path)| route.matches(method, path)))
+            .map(|route| format!("{} {}", route.method, route.full_path()))
+            .collect();
+        assert!(unexercised.is_empty(), "routes without a built-in contract step: {:?}", unexercised);
+    }
+
+    #[test]
+    fn test_case_ids_are_unique() {
+        let cases = builtin_cases();
+        let mut ids: Vec<&str> = cases.iter().map(|case| case.id.as_str()).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), cases.len());
+    }
+}
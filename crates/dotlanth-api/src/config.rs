@@ -16,7 +16,21 @@
 
 //! Configuration management for the REST API gateway
 
+use crate::admission::AdmissionConfig;
+use crate::api_keys::ApiKeyConfig;
+use crate::audit::AuditConfig;
+use crate::cors::CorsConfig;
+use crate::grpc_pool::PoolConfig;
+use crate::rate_limiting::PriorityRateLimitConfig;
+use crate::readiness::ReadinessConfig;
+use crate::response_cache::ResponseCacheConfig;
+use crate::security::SecurityHeadersConfig;
+use crate::transactions::TransactionConfig;
+use crate::uploads::UploadConfig;
+use crate::versioning::ApiVersionsConfig;
+use crate::websocket_mux::MultiplexConfig;
 use std::env;
+use std::path::PathBuf;
 
 /// Configuration for the REST API gateway
 #[derive(Debug, Clone)]
@@ -27,17 +41,23 @@ pub struct Config {
     /// Address of the gRPC VM service
     pub vm_service_address: String,
 
+    /// Channel pool and retry settings for the VM service
+    pub vm_pool: PoolConfig,
+
     /// Address of the gRPC Database service (via VM service)
     pub db_service_address: String,
 
     /// JWT secret key for authentication
     pub jwt_secret: String,
 
-    /// Enable CORS for web clients
-    pub cors_enabled: bool,
+    /// Cross-origin access for web clients
+    pub cors: CorsConfig,
+
+    /// Security headers added to every response
+    pub security_headers: SecurityHeadersConfig,
 
-    /// Allowed CORS origins
-    pub cors_origins: Vec<String>,
+    /// REST API path versions and their deprecation schedule
+    pub api_versions: ApiVersionsConfig,
 
     /// Request timeout in seconds
     pub request_timeout_secs: u64,
@@ -50,6 +70,45 @@ pub struct Config {
 
     /// OpenAPI documentation path
     pub openapi_path: String,
+
+    /// Per-API-key rate limits
+    pub rate_limit: PriorityRateLimitConfig,
+
+    /// JSON file overriding `rate_limit`, reloaded when it changes
+    pub rate_limit_config_path: Option<PathBuf>,
+
+    /// Reject execution inputs that the dot's ABI does not declare
+    pub abi_strict_validation: bool,
+
+    /// Maximum age of a cached dot ABI in seconds
+    pub abi_cache_ttl_secs: u64,
+
+    /// Caching of GET responses
+    pub response_cache: ResponseCacheConfig,
+
+    /// Limits for multiplexed WebSocket channels
+    pub websocket_multiplex: MultiplexConfig,
+
+    /// Seconds in-flight requests get to finish after SIGTERM/SIGINT
+    pub shutdown_grace_period_secs: u64,
+
+    /// Audit log of mutating requests
+    pub audit: AuditConfig,
+
+    /// API keys managed through the admin endpoints
+    pub api_keys: ApiKeyConfig,
+
+    /// Limits for document transactions opened through the API
+    pub transactions: TransactionConfig,
+
+    /// Limits for dot deployments uploaded as multipart bodies
+    pub deploy_uploads: UploadConfig,
+
+    /// Concurrency limits and queueing of dot executions
+    pub admission: AdmissionConfig,
+
+    /// Dependency probes behind `/readyz`
+    pub readiness: ReadinessConfig,
 }
 
 impl Default for Config {
@@ -57,14 +116,29 @@ impl Default for Config {
         Self {
             bind_address: "0.0.0.0:8080".to_string(),
             vm_service_address: "http://127.0.0.1:50051".to_string(),
+            vm_pool: PoolConfig::default(),
             db_service_address: "http://127.0.0.1:50051".to_string(), // VM service handles DB operations
             jwt_secret: "default-secret-change-in-production".to_string(),
-            cors_enabled: true,
-            cors_origins: vec!["http://localhost:3000".to_string()],
+            cors: CorsConfig::default(),
+            security_headers: SecurityHeadersConfig::default(),
+            api_versions: ApiVersionsConfig::default(),
             request_timeout_secs: 30,
             max_body_size: 10 * 1024 * 1024, // 10MB
             openapi_enabled: true,
             openapi_path: "/docs".to_string(),
+            rate_limit: PriorityRateLimitConfig::default(),
+            rate_limit_config_path: None,
+            abi_strict_validation: false,
+            abi_cache_ttl_secs: 300,
+            response_cache: ResponseCacheConfig::default(),
+            websocket_multiplex: MultiplexConfig::default(),
+            shutdown_grace_period_secs: 30,
+            audit: AuditConfig::default(),
+            api_keys: ApiKeyConfig::default(),
+            transactions: TransactionConfig::default(),
+            deploy_uploads: UploadConfig::default(),
+            admission: AdmissionConfig::default(),
+            readiness: ReadinessConfig::default(),
         }
     }
 }
@@ -77,15 +151,17 @@ impl Config {
 
             vm_service_address: env::var("DOTLANTH_VM_SERVICE_ADDRESS").unwrap_or_else(|_| "http://127.0.0.1:50051".to_string()),
 
+            vm_pool: PoolConfig::from_env(),
+
             db_service_address: env::var("DOTLANTH_DB_SERVICE_ADDRESS").unwrap_or_else(|_| "http://127.0.0.1:50051".to_string()),
 
             jwt_secret: env::var("DOTLANTH_JWT_SECRET").unwrap_or_else(|_| "default-secret-change-in-production".to_string()),
 
-            cors_enabled: env::var("DOTLANTH_CORS_ENABLED").map(|v| v.parse().unwrap_or(true)).unwrap_or(true),
+            cors: CorsConfig::from_env(),
+
+            security_headers: SecurityHeadersConfig::from_env(),
 
-            cors_origins: env::var("DOTLANTH_CORS_ORIGINS")
-                .map(|v| v.split(',').map(|s| s.trim().to_string()).collect())
-                .unwrap_or_else(|_| vec!["http://localhost:3000".to_string()]),
+            api_versions: ApiVersionsConfig::from_env(),
 
             request_timeout_secs: env::var("DOTLANTH_REQUEST_TIMEOUT_SECS").map(|v| v.parse().unwrap_or(30)).unwrap_or(30),
 
@@ -94,6 +170,32 @@ impl Config {
             openapi_enabled: env::var("DOTLANTH_OPENAPI_ENABLED").map(|v| v.parse().unwrap_or(true)).unwrap_or(true),
 
             openapi_path: env::var("DOTLANTH_OPENAPI_PATH").unwrap_or_else(|_| "/docs".to_string()),
+
+            rate_limit: PriorityRateLimitConfig::from_env(),
+
+            rate_limit_config_path: env::var("DOTLANTH_RATE_LIMIT_CONFIG").ok().map(PathBuf::from),
+
+            abi_strict_validation: env::var("DOTLANTH_ABI_STRICT_VALIDATION").map(|v| v.parse().unwrap_or(false)).unwrap_or(false),
+
+            abi_cache_ttl_secs: env::var("DOTLANTH_ABI_CACHE_TTL_SECS").map(|v| v.parse().unwrap_or(300)).unwrap_or(300),
+
+            response_cache: ResponseCacheConfig::from_env(),
+
+            websocket_multiplex: MultiplexConfig::from_env(),
+
+            shutdown_grace_period_secs: env::var("DOTLANTH_SHUTDOWN_GRACE_PERIOD_SECS").map(|v| v.parse().unwrap_or(30)).unwrap_or(30),
+
+            audit: AuditConfig::from_env(),
+
+            api_keys: ApiKeyConfig::from_env(),
+
+            transactions: TransactionConfig::from_env(),
+
+            deploy_uploads: UploadConfig::from_env(),
+
+            admission: AdmissionConfig::from_env(),
+
+            readiness: ReadinessConfig::from_env(),
         }
     }
 }
@@ -0,0 +1,532 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Cross-origin resource sharing for browser clients
+//!
+//! Allowed origins are matched exactly, against `*`, or against a
+//! `scheme://*.domain[:port]` pattern admitting any subdomain of `domain` (but not
+//! `domain` itself). Preflight requests are answered before authentication with the
+//! methods the requested route actually serves. Browsers do not apply CORS to
+//! WebSocket upgrades, so cross-origin upgrades are checked against the same
+//! allowed origins here. Requests without an `Origin` header are not browser
+//! cross-origin requests and pass through untouched.
+
+use crate::error::ApiError;
+use crate::grpc_pool::parse_env;
+use crate::openapi::{self, OPENAPI_JSON_PATH};
+use dotvm_common::telemetry::{REQUEST_ID_HEADER, TRACEPARENT_HEADER};
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::header::{
+    ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS,
+    ACCESS_CONTROL_REQUEST_METHOD, HeaderMap, HeaderName, HeaderValue, ORIGIN, UPGRADE, VARY,
+};
+use hyper::{Method, Request, Response, StatusCode};
+use thiserror::Error;
+
+/// Routes served outside the OpenAPI route table
+const UNREGISTERED_ROUTES: &[(&str, Method)] = &[
+    ("/api/v1/ws", Method::GET),
+    ("/graphql", Method::GET),
+    ("/graphql", Method::POST),
+    ("/playground", Method::GET),
+    ("/versions", Method::GET),
+    (OPENAPI_JSON_PATH, Method::GET),
+];
+
+/// CORS settings
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    /// Answer preflights and add CORS headers; when disabled the gateway does neither
+    pub enabled: bool,
+    /// Origins allowed to call the API: `*`, `scheme://host[:port]` or `scheme://*.domain[:port]`
+    pub allowed_origins: Vec<String>,
+    /// Methods cross-origin requests may use, narrowed per route in preflight responses
+    pub allowed_methods: Vec<String>,
+    /// Request headers cross-origin requests may send, or `*` for any
+    pub allowed_headers: Vec<String>,
+    /// How long browsers may cache a preflight response
+    pub max_age_secs: u64,
+    /// Allow cookies and authorization headers on cross-origin requests
+    pub allow_credentials: bool,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            allowed_origins: vec!["http://localhost:3000".to_string()],
+            allowed_methods: ["GET", "POST", "PUT", "DELETE"].map(String::from).to_vec(),
+            allowed_headers: [
+                "authorization",
+                "content-type",
+                "x-api-key",
+                "if-none-match",
+                "last-event-id",
+                "api-version",
+                REQUEST_ID_HEADER,
+                TRACEPARENT_HEADER,
+            ]
+            .map(String::from)
+            .to_vec(),
+            max_age_secs: 600,
+            allow_credentials: false,
+        }
+    }
+}
+
+impl CorsConfig {
+    /// Load CORS settings from `DOTLANTH_CORS_*` variables
+    ///
+    /// `DOTLANTH_CORS_ORIGINS`, `DOTLANTH_CORS_METHODS` and `DOTLANTH_CORS_HEADERS`
+    /// take comma separated lists that replace the defaults.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        let list = |name: &str| parse_env::<String>(name).map(|value| value.split(',').map(str::trim).filter(|item| !item.is_empty()).map(str::to_string).collect());
+
+        if let Some(enabled) = parse_env("DOTLANTH_CORS_ENABLED") {
+            config.enabled = enabled;
+        }
+        if let Some(origins) = list("DOTLANTH_CORS_ORIGINS") {
+            config.allowed_origins = origins;
+        }
+        if let Some(methods) = list("DOTLANTH_CORS_METHODS") {
+            config.allowed_methods = methods;
+        }
+        if let Some(headers) = list("DOTLANTH_CORS_HEADERS") {
+            config.allowed_headers = headers;
+        }
+        if let Some(max_age_secs) = parse_env("DOTLANTH_CORS_MAX_AGE_SECS") {
+            config.max_age_secs = max_age_secs;
+        }
+        if let Some(allow_credentials) = parse_env("DOTLANTH_CORS_ALLOW_CREDENTIALS") {
+            config.allow_credentials = allow_credentials;
+        }
+
+        config
+    }
+}
+
+/// A CORS configuration that cannot be enforced
+#[derive(Debug, Error, PartialEq)]
+pub enum CorsConfigError {
+    #[error("credentials cannot be allowed for any origin ('*'); list the trusted origins instead")]
+    CredentialsWithAnyOrigin,
+
+    #[error("credentials cannot be allowed with any request header ('*'); list the allowed headers instead")]
+    CredentialsWithAnyHeader,
+
+    #[error("invalid allowed origin '{0}': expected '*', 'scheme://host[:port]' or 'scheme://*.domain[:port]'")]
+    InvalidOrigin(String),
+
+    #[error("invalid allowed method '{0}'")]
+    InvalidMethod(String),
+
+    #[error("invalid allowed header '{0}'")]
+    InvalidHeader(String),
+}
+
+/// An allowed origin
+#[derive(Debug, Clone, PartialEq)]
+enum OriginPattern {
+    Any,
+    Exact(String),
+    /// Any subdomain: `prefix` is `scheme://`, `suffix` is `.domain[:port]`
+    Subdomain {
+        prefix: String,
+        suffix: String,
+    },
+}
+
+impl OriginPattern {
+    fn parse(pattern: &str) -> Result<Self, CorsConfigError> {
+        if pattern == "*" {
+            return Ok(Self::Any);
+        }
+        let invalid = || CorsConfigError::InvalidOrigin(pattern.to_string());
+        let pattern = pattern.to_ascii_lowercase();
+        let (scheme, authority) = pattern.split_once("://").ok_or_else(invalid)?;
+        if scheme.is_empty() || !scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.')) {
+            return Err(invalid());
+        }
+
+        match authority.strip_prefix("*.") {
+            Some(domain) if is_authority(domain) => Ok(Self::Subdomain {
+                prefix: format!("{}://", scheme),
+                suffix: format!(".{}", domain),
+            }),
+            None if is_authority(authority) => Ok(Self::Exact(pattern)),
+            _ => Err(invalid()),
+        }
+    }
+
+    fn matches(&self, origin: &str) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Exact(exact) => origin.eq_ignore_ascii_case(exact),
+            Self::Subdomain { prefix, suffix } => {
+                let origin = origin.to_ascii_lowercase();
+                origin.strip_prefix(prefix.as_str()).and_then(|host| host.strip_suffix(suffix.as_str())).is_some_and(is_host)
+            }
+        }
+    }
+}
+
+/// `host[:port]` without a path or wildcard
+fn is_authority(authority: &str) -> bool {
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (host, Some(port)),
+        None => (authority, None),
+    };
+    is_host(host) && port.is_none_or(|port| !port.is_empty() && port.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn is_host(host: &str) -> bool {
+    !host.is_empty() && !host.starts_with('.') && !host.ends_with('.') && host.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.'))
+}
+
+/// Request headers a cross-origin request may send
+#[derive(Debug, Clone)]
+enum AllowedHeaders {
+    Any,
+    List(Vec<HeaderName>),
+}
+
+/// Validated CORS settings applied by the server to every request
+#[derive(Debug, Clone)]
+pub struct CorsPolicy {
+    enabled: bool,
+    origins: Vec<OriginPattern>,
+    methods: Vec<Method>,
+    headers: AllowedHeaders,
+    max_age: HeaderValue,
+    allow_credentials: bool,
+}
+
+impl CorsPolicy {
+    /// Validate a CORS configuration, rejecting ones browsers would refuse or that are ambiguous
+    pub fn new(config: &CorsConfig) -> Result<Self, CorsConfigError> {
+        let origins = config.allowed_origins.iter().map(|origin| OriginPattern::parse(origin)).collect::<Result<Vec<_>, _>>()?;
+        let methods = config
+            .allowed_methods
+            .iter()
+            .map(|method| Method::from_bytes(method.to_ascii_uppercase().as_bytes()).map_err(|_| CorsConfigError::InvalidMethod(method.clone())))
+            .collect::<Result<Vec<_>, _>>()?;
+        let headers = if config.allowed_headers.iter().any(|header| header == "*") {
+            AllowedHeaders::Any
+        } else {
+            AllowedHeaders::List(
+                config
+                    .allowed_headers
+                    .iter()
+                    .map(|header| HeaderName::from_bytes(header.as_bytes()).map_err(|_| CorsConfigError::InvalidHeader(header.clone())))
+                    .collect::<Result<Vec<_>, _>>()?,
+            )
+        };
+
+        // Browsers ignore a wildcard in credentialed responses, so accepting it would
+        // silently break clients or tempt a reflect-any-origin workaround
+        if config.allow_credentials {
+            if origins.contains(&OriginPattern::Any) {
+                return Err(CorsConfigError::CredentialsWithAnyOrigin);
+            }
+            if matches!(headers, AllowedHeaders::Any) {
+                return Err(CorsConfigError::CredentialsWithAnyHeader);
+            }
+        }
+
+        Ok(Self {
+            enabled: config.enabled,
+            origins,
+            methods,
+            headers,
+            max_age: HeaderValue::from(config.max_age_secs),
+            allow_credentials: config.allow_credentials,
+        })
+    }
+
+    /// Whether requests from an origin may use the API
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        self.origins.iter().any(|pattern| pattern.matches(origin))
+    }
+
+    /// Answer preflights and refuse WebSocket upgrades from disallowed origins
+    ///
+    /// Returns `None` for requests that should be routed as usual.
+    pub fn intercept<B>(&self, req: &Request<B>) -> Option<Result<Response<Full<Bytes>>, ApiError>> {
+        if !self.enabled {
+            return None;
+        }
+        let origin = req.headers().get(ORIGIN)?;
+
+        if req.method() == Method::OPTIONS && req.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD) {
+            return Some(self.preflight(origin, req));
+        }
+
+        let upgrade = req.headers().get(UPGRADE).and_then(|value| value.to_str().ok());
+        if upgrade.is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket")) && !self.origin_allowed(origin) {
+            return Some(Err(ApiError::Forbidden {
+                message: format!("WebSocket connections from origin {:?} are not allowed", origin),
+            }));
+        }
+
+        None
+    }
+
+    /// Add CORS headers to the response of a request from an allowed origin
+    pub fn apply(&self, origin: Option<&HeaderValue>, headers: &mut HeaderMap) {
+        let Some(origin) = origin.filter(|origin| self.enabled && self.origin_allowed(origin)) else {
+            return;
+        };
+        if headers.contains_key(ACCESS_CONTROL_ALLOW_ORIGIN) {
+            return;
+        }
+        self.allow_origin(origin, headers);
+    }
+
+    fn preflight<B>(&self, origin: &HeaderValue, req: &Request<B>) -> Result<Response<Full<Bytes>>, ApiError> {
+        if !self.origin_allowed(origin) {
+            return Err(ApiError::Forbidden {
+                message: format!("Origin {:?} is not allowed", origin),
+            });
+        }
+
+        let path = req.uri().path();
+        let route_methods = route_methods(path);
+        if route_methods.is_empty() {
+            return Err(ApiError::NotFound {
+                message: format!("No route for {}", path),
+            });
+        }
+        let methods: Vec<&Method> = route_methods.iter().filter(|method| self.methods.contains(method)).collect();
+        let requested = req.headers().get(ACCESS_CONTROL_REQUEST_METHOD).and_then(|value| Method::from_bytes(value.as_bytes()).ok());
+        if !requested.is_some_and(|requested| methods.contains(&&requested)) {
+            return Err(ApiError::MethodNotAllowed {
+                message: format!("{:?} is not allowed for cross-origin requests to {}", req.headers()[ACCESS_CONTROL_REQUEST_METHOD], path),
+            });
+        }
+
+        let requested_headers = req.headers().get(ACCESS_CONTROL_REQUEST_HEADERS).and_then(|value| value.to_str().ok()).unwrap_or("");
+        let allowed_headers = match &self.headers {
+            AllowedHeaders::Any => HeaderValue::from_static("*"),
+            AllowedHeaders::List(allowed) => {
+                let requested_headers = requested_headers.split(',').map(str::trim).filter(|header| !header.is_empty());
+                if let Some(header) = requested_headers.clone().find(|header| !allowed.iter().any(|allowed| allowed.as_str().eq_ignore_ascii_case(header))) {
+                    return Err(ApiError::Forbidden {
+                        message: format!("Request header '{}' is not allowed for cross-origin requests", header),
+                    });
+                }
+                join(allowed.iter().map(HeaderName::as_str))
+            }
+        };
+
+        let mut response = Response::builder().status(StatusCode::NO_CONTENT).body(Full::new(Bytes::new()))?;
+        let headers = response.headers_mut();
+        self.allow_origin(origin, headers);
+        headers.insert(ACCESS_CONTROL_ALLOW_METHODS, join(methods.iter().map(|method| method.as_str())));
+        headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, allowed_headers);
+        headers.insert(ACCESS_CONTROL_MAX_AGE, self.max_age.clone());
+        headers.append(VARY, HeaderValue::from_static("access-control-request-method, access-control-request-headers"));
+        Ok(response)
+    }
+
+    fn origin_allowed(&self, origin: &HeaderValue) -> bool {
+        origin.to_str().is_ok_and(|origin| self.allows_origin(origin))
+    }
+
+    fn allow_origin(&self, origin: &HeaderValue, headers: &mut HeaderMap) {
+        // Without credentials a wildcard policy needs no per-origin response, which keeps it cacheable
+        if !self.allow_credentials && self.origins.contains(&OriginPattern::Any) {
+            headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+            return;
+        }
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+        headers.append(VARY, HeaderValue::from_static("origin"));
+        if self.allow_credentials {
+            headers.insert(ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+        }
+    }
+}
+
+/// Methods served on a concrete request path, under any API version for unversioned `/api/` paths
+fn route_methods(path: &str) -> Vec<Method> {
+    let (version, below_prefix) = match openapi::split_version_prefix(path) {
+        Some((version, rest)) => (Some(version), Some(rest)),
+        None => (None, path.strip_prefix("/api").filter(|rest| rest.starts_with('/'))),
+    };
+    let registered = openapi::ROUTES
+        .iter()
+        .filter(|route| below_prefix.is_some_and(|rest| version.is_none_or(|version| route.serves(version)) && openapi::template_matches(route.path, rest)))
+        .map(|route| route.method.clone());
+    let unregistered = UNREGISTERED_ROUTES.iter().filter(|(route, _)| *route == path).map(|(_, method)| method.clone());
+    registered.chain(unregistered).fold(Vec::new(), |mut methods, method| {
+        if !methods.contains(&method) {
+            methods.push(method);
+        }
+        methods
+    })
+}
+
+fn join<'a>(items: impl Iterator<Item = &'a str>) -> HeaderValue {
+    HeaderValue::from_str(&items.collect::<Vec<_>>().join(", ")).expect("method and header names are valid header values")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(origins: &[&str]) -> CorsPolicy {
+        CorsPolicy::new(&CorsConfig {
+            allowed_origins: origins.iter().map(|origin| origin.to_string()).collect(),
+            ..CorsConfig::default()
+        })
+        .unwrap()
+    }
+
+    fn preflight(path: &str, origin: &str, method: &str, headers: &str) -> Request<()> {
+        Request::builder()
+            .method(Method::OPTIONS)
+            .uri(path)
+            .header(ORIGIN, origin)
+            .header(ACCESS_CONTROL_REQUEST_METHOD, method)
+            .header(ACCESS_CONTROL_REQUEST_HEADERS, headers)
+            .body(())
+            .unwrap()
+    }
+
+    fn status(result: Option<Result<Response<Full<Bytes>>, ApiError>>) -> StatusCode {
+        match result.expect("request was intercepted") {
+            Ok(response) => response.status(),
+            Err(e) => e.status_code(),
+        }
+    }
+
+    #[test]
+    fn test_origin_patterns() {
+        let policy = policy(&["https://app.example.com", "https://*.example.org", "http://*.localhost:3000"]);
+
+        assert!(policy.allows_origin("https://app.example.com"));
+        assert!(policy.allows_origin("HTTPS://App.Example.com"));
+        assert!(!policy.allows_origin("http://app.example.com"));
+        assert!(policy.allows_origin("https://a.example.org"));
+        assert!(policy.allows_origin("https://a.b.example.org"));
+        assert!(!policy.allows_origin("https://example.org"));
+        assert!(!policy.allows_origin("https://evilexample.org"));
+        assert!(!policy.allows_origin("https://a.example.org:8443"));
+        assert!(!policy.allows_origin("https://evil.com/.example.org"));
+        assert!(policy.allows_origin("http://web.localhost:3000"));
+        assert!(!policy.allows_origin("http://web.localhost:3001"));
+    }
+
+    #[test]
+    fn test_preflight_from_allowed_origin() {
+        let policy = policy(&["https://*.example.com"]);
+        let req = preflight("/api/v1/collections/users/documents/42", "https://app.example.com", "PUT", "Authorization, Content-Type");
+
+        let response = policy.intercept(&req).unwrap().unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let headers = response.headers();
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "https://app.example.com");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_METHODS], "GET, PUT, DELETE");
+        assert!(headers[ACCESS_CONTROL_ALLOW_HEADERS].to_str().unwrap().contains("authorization"));
+        assert_eq!(headers[ACCESS_CONTROL_MAX_AGE], "600");
+        assert!(!headers.contains_key(ACCESS_CONTROL_ALLOW_CREDENTIALS));
+        assert!(headers.get_all(VARY).iter().any(|vary| vary == "origin"));
+    }
+
+    #[test]
+    fn test_preflight_rejections() {
+        let policy = policy(&["https://*.example.com"]);
+
+        assert_eq!(status(policy.intercept(&preflight("/api/v1/collections", "https://evil.com", "GET", ""))), StatusCode::FORBIDDEN);
+        assert_eq!(
+            status(policy.intercept(&preflight("/api/v1/collections", "https://app.example.com", "DELETE", ""))),
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+        assert_eq!(
+            status(policy.intercept(&preflight("/api/v1/collections", "https://app.example.com", "GET", "x-secret"))),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(status(policy.intercept(&preflight("/api/v1/nothing", "https://app.example.com", "GET", ""))), StatusCode::NOT_FOUND);
+
+        // Same-origin and non-browser requests are left to the router
+        let options = Request::builder().method(Method::OPTIONS).uri("/api/v1/collections").body(()).unwrap();
+        assert!(policy.intercept(&options).is_none());
+    }
+
+    #[test]
+    fn test_websocket_upgrades_follow_origin_policy() {
+        let policy = policy(&["https://*.example.com"]);
+        let upgrade = |origin: Option<&str>| {
+            let mut req = Request::builder().method(Method::GET).uri("/api/v1/ws").header(UPGRADE, "websocket");
+            if let Some(origin) = origin {
+                req = req.header(ORIGIN, origin);
+            }
+            req.body(()).unwrap()
+        };
+
+        assert_eq!(status(policy.intercept(&upgrade(Some("https://evil.com")))), StatusCode::FORBIDDEN);
+        assert!(policy.intercept(&upgrade(Some("https://app.example.com"))).is_none());
+        assert!(policy.intercept(&upgrade(None)).is_none());
+    }
+
+    #[test]
+    fn test_response_headers() {
+        let credentialed = CorsPolicy::new(&CorsConfig {
+            allowed_origins: vec!["https://app.example.com".to_string()],
+            allow_credentials: true,
+            ..CorsConfig::default()
+        })
+        .unwrap();
+        let mut headers = HeaderMap::new();
+        credentialed.apply(Some(&HeaderValue::from_static("https://app.example.com")), &mut headers);
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "https://app.example.com");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+
+        let mut headers = HeaderMap::new();
+        credentialed.apply(Some(&HeaderValue::from_static("https://evil.com")), &mut headers);
+        assert!(headers.is_empty());
+
+        let mut headers = HeaderMap::new();
+        policy(&["*"]).apply(Some(&HeaderValue::from_static("https://anywhere.io")), &mut headers);
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        assert!(!headers.contains_key(VARY));
+    }
+
+    #[test]
+    fn test_misconfiguration_rejected() {
+        let config = |origins: &[&str], headers: &[&str], allow_credentials: bool| CorsConfig {
+            allowed_origins: origins.iter().map(|origin| origin.to_string()).collect(),
+            allowed_headers: headers.iter().map(|header| header.to_string()).collect(),
+            allow_credentials,
+            ..CorsConfig::default()
+        };
+
+        assert_eq!(CorsPolicy::new(&config(&["*"], &["authorization"], true)).unwrap_err(), CorsConfigError::CredentialsWithAnyOrigin);
+        assert_eq!(CorsPolicy::new(&config(&["https://a.io"], &["*"], true)).unwrap_err(), CorsConfigError::CredentialsWithAnyHeader);
+        assert!(CorsPolicy::new(&config(&["*"], &["*"], false)).is_ok());
+        for origin in ["example.com", "https://*", "https://a.*.com", "https://a.com/path", "https://a.com:"] {
+            assert_eq!(CorsPolicy::new(&config(&[origin], &[], false)).unwrap_err(), CorsConfigError::InvalidOrigin(origin.to_string()));
+        }
+        assert!(matches!(
+            CorsPolicy::new(&CorsConfig {
+                allowed_methods: vec!["GE T".to_string()],
+                ..CorsConfig::default()
+            }),
+            Err(CorsConfigError::InvalidMethod(_))
+        ));
+    }
+}
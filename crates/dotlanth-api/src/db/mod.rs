@@ -0,0 +1,756 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Database client for interacting with DotDB core components
+
+pub mod pipeline;
+
+use crate::error::{ApiError, ApiResult};
+use crate::models::{Collection, CreateDocumentResponse, Document, DocumentList, PaginationInfo, SearchResults, SnapshotInfo, TransactionInfo, TransactionIsolation};
+use crate::snapshots::OpenSnapshots;
+use crate::transactions::{OpenTransactions, TransactionConfig, abort_error};
+use chrono::{DateTime, Utc};
+use dotdb_core::document::collection::{CollectionManager, create_in_memory_collection_manager};
+use dotdb_core::document::{AggregationResult, AggregationSpec, DocumentError, DocumentId, ProjectedDocument, Projection, ReadSnapshot, ScanOptions};
+use dotdb_core::storage_engine::IsolationLevel;
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// How documents are read by listing and search
+#[derive(Debug, Clone, Default)]
+pub struct ReadOptions {
+    /// Read the collection as it was at this time rather than now
+    pub as_of: Option<DateTime<Utc>>,
+    /// Read the collection through this snapshot rather than now
+    pub snapshot: Option<Arc<ReadSnapshot>>,
+    /// Reduce each document to these paths
+    pub fields: Option<Projection>,
+}
+
+/// Database client for DotDB operations
+#[derive(Clone)]
+pub struct DatabaseClient {
+    collection_manager: Arc<Mutex<CollectionManager>>,
+    transactions: Arc<OpenTransactions>,
+    snapshots: Arc<OpenSnapshots>,
+}
+
+impl DatabaseClient {
+    /// Create a new database client
+    pub fn new(_db_service_address: &str) -> ApiResult<Self> {
+        info!("Creating database client with DotDB core integration");
+
+        // Create an in-memory collection manager for now
+        // In production, this could be persistent storage or a gRPC client
+        let collection_manager = create_in_memory_collection_manager().map_err(|e| ApiError::InternalServerError {
+            message: format!("Failed to create collection manager: {}", e),
+        })?;
+
+        info!("Successfully created database client");
+
+        Ok(Self {
+            collection_manager: Arc::new(Mutex::new(collection_manager)),
+            transactions: Arc::new(OpenTransactions::new(TransactionConfig::default())),
+            snapshots: Arc::new(OpenSnapshots::new()),
+        })
+    }
+
+    /// Apply the given limits to transactions begun through this client
+    pub fn with_transactions(mut self, config: TransactionConfig) -> Self {
+        self.transactions = Arc::new(OpenTransactions::new(config));
+        self
+    }
+
+    /// Transactions begun through this client and not yet finished
+    pub fn transactions(&self) -> Arc<OpenTransactions> {
+        self.transactions.clone()
+    }
+
+    /// Snapshots taken through this client and not yet released
+    pub fn snapshots(&self) -> Arc<OpenSnapshots> {
+        self.snapshots.clone()
+    }
+
+    /// List all collections
+    pub async fn list_collections(&self) -> ApiResult<Vec<Collection>> {
+        let manager = self.collection_manager.lock().await;
+
+        let collection_names = manager.list_collections().map_err(|e| self.convert_document_error(e))?;
+
+        let mut result = Vec::new();
+        for name in collection_names {
+            // CollectionManager doesn't have count_documents method, so we get all IDs and count them
+            let doc_ids = manager.list_document_ids(&name).map_err(|e| self.convert_document_error(e))?;
+            let document_count = doc_ids.len() as u64;
+
+            let collection = Collection {
+                name,
+                document_count,
+                created_at: Utc::now(), // DotDB doesn't track collection creation time yet
+                updated_at: Utc::now(),
+            };
+            result.push(collection);
+        }
+
+        Ok(result)
+    }
+
+    /// Create a new collection
+    pub async fn create_collection(&self, name: &str) -> ApiResult<Collection> {
+        let manager = self.collection_manager.lock().await;
+
+        // Check if collection already exists
+        if manager.collection_exists(name).map_err(|e| self.convert_document_error(e))? {
+            return Err(ApiError::Conflict {
+                message: format!("Collection '{}' already exists", name),
+            });
+        }
+
+        manager.create_collection(name).map_err(|e| self.convert_document_error(e))?;
+
+        info!("Created collection: {}", name);
+
+        Ok(Collection {
+            name: name.to_string(),
+            document_count: 0,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        })
+    }
+
+    /// Create a collection unless it already exists
+    pub async fn ensure_collection(&self, name: &str) -> ApiResult<()> {
+        let manager = self.collection_manager.lock().await;
+
+        if !manager.collection_exists(name).map_err(|e| self.convert_document_error(e))? {
+            manager.create_collection(name).map_err(|e| self.convert_document_error(e))?;
+            info!("Created collection: {}", name);
+        }
+
+        Ok(())
+    }
+
+    /// Delete a collection
+    pub async fn delete_collection(&self, name: &str) -> ApiResult<()> {
+        let manager = self.collection_manager.lock().await;
+
+        let deleted = manager.delete_collection(name).map_err(|e| self.convert_document_error(e))?;
+
+        if !deleted {
+            return Err(ApiError::NotFound {
+                message: format!("Collection '{}' not found", name),
+            });
+        }
+
+        info!("Deleted collection: {}", name);
+        Ok(())
+    }
+
+    /// Get documents from a collection with pagination, ordered by `sort_field` (by ID without one)
+    /// and read as `read` asks
+    ///
+    /// Read as of a past time or through a snapshot, a collection that has since been
+    /// deleted still lists the documents it held then.
+    pub async fn get_documents(&self, collection_name: &str, page: u32, page_size: u32, sort_field: Option<String>, descending: bool, read: &ReadOptions) -> ApiResult<DocumentList> {
+        let manager = self.collection_manager.lock().await;
+
+        // Check if collection exists
+        if read.as_of.is_none() && read.snapshot.is_none() && !manager.collection_exists(collection_name).map_err(|e| self.convert_document_error(e))? {
+            return Err(ApiError::NotFound {
+                message: format!("Collection '{}' not found", collection_name),
+            });
+        }
+
+        let options = ScanOptions {
+            sort_field,
+            descending,
+            limit: Some(page_size as usize),
+            offset: ((page - 1) * page_size) as usize,
+        };
+        let (total_items, listed) = match (&read.snapshot, read.as_of) {
+            (Some(snapshot), _) => {
+                let total_items = snapshot.count(collection_name).map_err(|e| self.convert_document_error(e))?;
+                (total_items, snapshot.list_documents(collection_name, &options))
+            }
+            (None, Some(as_of)) => {
+                let timestamp = as_of_timestamp(as_of)?;
+                let total_items = manager.count_as_of(collection_name, timestamp).map_err(|e| self.convert_document_error(e))?;
+                (total_items, manager.list_documents_as_of(collection_name, &options, timestamp))
+            }
+            (None, None) => {
+                let total_items = manager.count(collection_name).map_err(|e| self.convert_document_error(e))?;
+                (total_items, manager.list_documents(collection_name, &options))
+            }
+        };
+        let total_items = total_items as u64;
+        let total_pages = ((total_items as f64) / (page_size as f64)).ceil() as u32;
+
+        let mut documents = Vec::new();
+        for (doc_id, content) in listed.map_err(|e| self.convert_document_error(e))? {
+            let (content, missing_fields) = project(doc_id.clone(), content, read.fields.as_ref());
+            documents.push(Document {
+                id: doc_id.to_string(), // DocumentId contains UUID, convert to string
+                content,
+                missing_fields,
+                created_at: Utc::now(), // DotDB doesn't store timestamps yet
+                updated_at: Utc::now(),
+                version: 1,
+            });
+        }
+
+        let pagination = PaginationInfo {
+            page,
+            page_size,
+            total_items,
+            total_pages,
+            has_next: page < total_pages,
+            has_previous: page > 1,
+        };
+
+        Ok(DocumentList { documents, pagination })
+    }
+
+    /// Every document in a collection as `(id, content)` pairs, or none if the collection does not exist
+    pub async fn scan_documents(&self, collection_name: &str) -> ApiResult<Vec<(String, Value)>> {
+        let manager = self.collection_manager.lock().await;
+
+        if !manager.collection_exists(collection_name).map_err(|e| self.convert_document_error(e))? {
+            return Ok(Vec::new());
+        }
+
+        let doc_ids = manager.list_document_ids(collection_name).map_err(|e| self.convert_document_error(e))?;
+        let mut documents = Vec::with_capacity(doc_ids.len());
+        for doc_id in doc_ids {
+            if let Some(content) = manager.get_value(collection_name, &doc_id).map_err(|e| self.convert_document_error(e))? {
+                documents.push((doc_id.to_string(), content));
+            }
+        }
+
+        Ok(documents)
+    }
+
+    /// Get a document by ID, as it was at `as_of` if given
+    pub async fn get_document(&self, collection_name: &str, document_id: &str, as_of: Option<DateTime<Utc>>) -> ApiResult<Document> {
+        let manager = self.collection_manager.lock().await;
+
+        let doc_id = DocumentId::from_string(document_id).map_err(|_| ApiError::BadRequest {
+            message: format!("Invalid document ID: {}", document_id),
+        })?;
+        // Past versions are read without their version number
+        let document = match as_of {
+            Some(as_of) => manager
+                .get_value_as_of(collection_name, &doc_id, as_of_timestamp(as_of)?)
+                .map(|content| content.map(|content| (content, 1))),
+            None => manager.get_value_with_version(collection_name, &doc_id),
+        };
+        let (content, version) = document.map_err(|e| self.convert_document_error(e))?.ok_or_else(|| ApiError::NotFound {
+            message: format!("Document '{}' not found in collection '{}'", document_id, collection_name),
+        })?;
+
+        Ok(Document {
+            id: document_id.to_string(),
+            content,
+            missing_fields: Vec::new(),
+            created_at: Utc::now(), // DotDB doesn't store timestamps yet
+            updated_at: Utc::now(),
+            version,
+        })
+    }
+
+    /// Get a document by ID through `snapshot`, as it was when the snapshot was taken
+    pub async fn get_document_in_snapshot(&self, snapshot: &ReadSnapshot, collection_name: &str, document_id: &str) -> ApiResult<Document> {
+        let doc_id = parse_document_id(document_id)?;
+        let content = snapshot
+            .get_value(collection_name, &doc_id)
+            .map_err(|e| self.convert_document_error(e))?
+            .ok_or_else(|| ApiError::NotFound {
+                message: format!("Document '{}' not found in collection '{}'", document_id, collection_name),
+            })?;
+
+        Ok(Document {
+            id: document_id.to_string(),
+            content,
+            missing_fields: Vec::new(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            version: 1,
+        })
+    }
+
+    /// Create a new document
+    pub async fn create_document(&self, collection_name: &str, content: Value) -> ApiResult<CreateDocumentResponse> {
+        let manager = self.collection_manager.lock().await;
+
+        let now = Utc::now();
+
+        // Create the document using DotDB (which generates its own ID)
+        let doc_id = manager.insert_value(collection_name, content).map_err(|e| self.convert_document_error(e))?;
+
+        let document_id = doc_id.to_string();
+        info!("Created document {} in collection: {}", document_id, collection_name);
+
+        Ok(CreateDocumentResponse { id: document_id, created_at: now })
+    }
+
+    /// Update a document
+    pub async fn update_document(&self, collection_name: &str, document_id: &str, content: Value) -> ApiResult<Document> {
+        let manager = self.collection_manager.lock().await;
+
+        let doc_id = DocumentId::from_string(document_id).map_err(|_| ApiError::BadRequest {
+            message: format!("Invalid document ID: {}", document_id),
+        })?;
+
+        // Check if document exists first
+        if manager.get_value(collection_name, &doc_id).map_err(|e| self.convert_document_error(e))?.is_none() {
+            return Err(ApiError::NotFound {
+                message: format!("Document '{}' not found in collection '{}'", document_id, collection_name),
+            });
+        }
+
+        // Update the document
+        manager.update_value(collection_name, &doc_id, content.clone()).map_err(|e| self.convert_document_error(e))?;
+        let version = manager
+            .get_value_with_version(collection_name, &doc_id)
+            .map_err(|e| self.convert_document_error(e))?
+            .map_or(1, |(_, version)| version);
+
+        info!("Updated document {} in collection: {}", document_id, collection_name);
+
+        Ok(Document {
+            id: document_id.to_string(),
+            content,
+            missing_fields: Vec::new(),
+            created_at: Utc::now(), // DotDB doesn't store timestamps yet
+            updated_at: Utc::now(),
+            version,
+        })
+    }
+
+    /// Update a document if it is still at `expected_version`
+    ///
+    /// Fails with [`ApiError::VersionConflict`] if another writer updated it first.
+    pub async fn update_document_if_version(&self, collection_name: &str, document_id: &str, content: Value, expected_version: u64) -> ApiResult<Document> {
+        let manager = self.collection_manager.lock().await;
+
+        let doc_id = parse_document_id(document_id)?;
+        let version = manager
+            .update_value_if_version(collection_name, &doc_id, content.clone(), expected_version)
+            .map_err(|e| self.convert_document_error(e))?;
+
+        info!("Updated document {} in collection {} from version {}", document_id, collection_name, expected_version);
+
+        Ok(Document {
+            id: document_id.to_string(),
+            content,
+            missing_fields: Vec::new(),
+            created_at: Utc::now(), // DotDB doesn't store timestamps yet
+            updated_at: Utc::now(),
+            version,
+        })
+    }
+
+    /// Delete a document
+    pub async fn delete_document(&self, collection_name: &str, document_id: &str) -> ApiResult<()> {
+        let manager = self.collection_manager.lock().await;
+
+        let doc_id = DocumentId::from_string(document_id).map_err(|_| ApiError::BadRequest {
+            message: format!("Invalid document ID: {}", document_id),
+        })?;
+        let deleted = manager.delete(collection_name, &doc_id).map_err(|e| self.convert_document_error(e))?;
+
+        if !deleted {
+            return Err(ApiError::NotFound {
+                message: format!("Document '{}' not found in collection '{}'", document_id, collection_name),
+            });
+        }
+
+        info!("Deleted document {} from collection: {}", document_id, collection_name);
+        Ok(())
+    }
+
+    /// Delete a document if it is still at `expected_version`
+    pub async fn delete_document_if_version(&self, collection_name: &str, document_id: &str, expected_version: u64) -> ApiResult<()> {
+        let manager = self.collection_manager.lock().await;
+
+        let doc_id = parse_document_id(document_id)?;
+        let deleted = manager.delete_if_version(collection_name, &doc_id, expected_version).map_err(|e| self.convert_document_error(e))?;
+
+        if !deleted {
+            return Err(ApiError::NotFound {
+                message: format!("Document '{}' not found in collection '{}'", document_id, collection_name),
+            });
+        }
+
+        info!("Deleted document {} from collection {} at version {}", document_id, collection_name, expected_version);
+        Ok(())
+    }
+
+    /// Begin a transaction owned by `owner`
+    pub async fn begin_transaction(&self, owner: &str, isolation: TransactionIsolation) -> ApiResult<TransactionInfo> {
+        let level = match isolation {
+            TransactionIsolation::ReadUncommitted => IsolationLevel::ReadUncommitted,
+            TransactionIsolation::ReadCommitted => IsolationLevel::ReadCommitted,
+            TransactionIsolation::RepeatableRead => IsolationLevel::RepeatableRead,
+            TransactionIsolation::Serializable => IsolationLevel::Serializable,
+        };
+        let txn = self.collection_manager.lock().await.begin_transaction(level);
+        let (id, expires_at) = self.transactions.insert(owner, txn);
+
+        info!("Began transaction {} for {}", id, owner);
+
+        Ok(TransactionInfo {
+            id: id.to_string(),
+            isolation,
+            expires_at,
+            max_operations: self.transactions.config().max_operations,
+        })
+    }
+
+    /// Pin a snapshot of every collection for `owner` to read through
+    pub async fn begin_snapshot(&self, owner: &str) -> ApiResult<SnapshotInfo> {
+        let manager = self.collection_manager.lock().await;
+        let snapshot = manager.begin_read_snapshot().map_err(|e| self.convert_document_error(e))?;
+        let max_age = manager.snapshot_max_age();
+        drop(manager);
+
+        let snapshot_at = DateTime::from_timestamp_nanos(snapshot.timestamp() as i64);
+        let token = self.snapshots.insert(owner, snapshot);
+
+        info!("Began snapshot {} at {} for {}", token, snapshot_at, owner);
+
+        Ok(SnapshotInfo {
+            token: token.to_string(),
+            snapshot_at,
+            expires_at: Utc::now() + chrono::Duration::from_std(max_age).unwrap_or(chrono::Duration::MAX),
+        })
+    }
+
+    /// The snapshot `token` of `owner`, to read through
+    pub fn read_snapshot(&self, token: &str, owner: &str) -> ApiResult<Arc<ReadSnapshot>> {
+        self.snapshots.get(token, owner)
+    }
+
+    /// Release the snapshot `token` of `owner`
+    pub async fn end_snapshot(&self, token: &str, owner: &str) -> ApiResult<()> {
+        self.snapshots.release(token, owner)?;
+
+        info!("Ended snapshot {}", token);
+        Ok(())
+    }
+
+    /// Commit the transaction `txn_id` of `owner`
+    pub async fn commit_transaction(&self, txn_id: &str, owner: &str) -> ApiResult<()> {
+        let txn = self.transactions.take(txn_id, owner)?;
+        txn.commit().map_err(|e| self.convert_document_error(e))?;
+
+        info!("Committed transaction {}", txn_id);
+        Ok(())
+    }
+
+    /// Abort the transaction `txn_id` of `owner`, discarding its writes
+    pub async fn abort_transaction(&self, txn_id: &str, owner: &str) -> ApiResult<()> {
+        self.transactions.abort(txn_id, owner)?;
+
+        info!("Aborted transaction {}", txn_id);
+        Ok(())
+    }
+
+    /// Get a document by ID within the transaction `txn_id` of `owner`
+    pub async fn get_document_in(&self, txn_id: &str, owner: &str, collection_name: &str, document_id: &str) -> ApiResult<Document> {
+        let doc_id = parse_document_id(document_id)?;
+        let content = self
+            .transactions
+            .run(txn_id, owner, |txn| txn.get_value(collection_name, &doc_id).map_err(|e| self.convert_document_error(e)))?
+            .ok_or_else(|| ApiError::NotFound {
+                message: format!("Document '{}' not found in collection '{}'", document_id, collection_name),
+            })?;
+
+        Ok(Document {
+            id: document_id.to_string(),
+            content,
+            missing_fields: Vec::new(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            version: 1,
+        })
+    }
+
+    /// Create a document within the transaction `txn_id` of `owner`
+    pub async fn create_document_in(&self, txn_id: &str, owner: &str, collection_name: &str, content: Value) -> ApiResult<CreateDocumentResponse> {
+        let now = Utc::now();
+        let doc_id = self
+            .transactions
+            .run(txn_id, owner, |txn| txn.insert_value(collection_name, content).map_err(|e| self.convert_document_error(e)))?;
+
+        info!("Created document {} in collection {} within transaction {}", doc_id, collection_name, txn_id);
+
+        Ok(CreateDocumentResponse {
+            id: doc_id.to_string(),
+            created_at: now,
+        })
+    }
+
+    /// Update a document within the transaction `txn_id` of `owner`
+    pub async fn update_document_in(&self, txn_id: &str, owner: &str, collection_name: &str, document_id: &str, content: Value) -> ApiResult<Document> {
+        let doc_id = parse_document_id(document_id)?;
+        self.transactions.run(txn_id, owner, |txn| {
+            txn.update_value(collection_name, &doc_id, content.clone()).map_err(|e| match e {
+                DocumentError::DocumentNotFound(_) => ApiError::NotFound {
+                    message: format!("Document '{}' not found in collection '{}'", document_id, collection_name),
+                },
+                e => self.convert_document_error(e),
+            })
+        })?;
+
+        info!("Updated document {} in collection {} within transaction {}", document_id, collection_name, txn_id);
+
+        Ok(Document {
+            id: document_id.to_string(),
+            content,
+            missing_fields: Vec::new(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            version: 2,
+        })
+    }
+
+    /// Delete a document within the transaction `txn_id` of `owner`
+    pub async fn delete_document_in(&self, txn_id: &str, owner: &str, collection_name: &str, document_id: &str) -> ApiResult<()> {
+        let doc_id = parse_document_id(document_id)?;
+        let deleted = self
+            .transactions
+            .run(txn_id, owner, |txn| txn.delete(collection_name, &doc_id).map_err(|e| self.convert_document_error(e)))?;
+
+        if !deleted {
+            return Err(ApiError::NotFound {
+                message: format!("Document '{}' not found in collection '{}'", document_id, collection_name),
+            });
+        }
+
+        info!("Deleted document {} from collection {} within transaction {}", document_id, collection_name, txn_id);
+        Ok(())
+    }
+
+    /// Search documents in a collection, read as `read` asks
+    ///
+    /// The search runs over whole documents; only the returned content is projected.
+    pub async fn search_documents(&self, collection_name: &str, query: &str, limit: Option<u32>, offset: Option<u32>, read: &ReadOptions) -> ApiResult<SearchResults> {
+        let manager = self.collection_manager.lock().await;
+        let start_time = std::time::Instant::now();
+
+        // Check if collection exists
+        if read.as_of.is_none() && read.snapshot.is_none() && !manager.collection_exists(collection_name).map_err(|e| self.convert_document_error(e))? {
+            return Err(ApiError::NotFound {
+                message: format!("Collection '{}' not found", collection_name),
+            });
+        }
+
+        // Get all documents in the collection for searching
+        let documents = match (&read.snapshot, read.as_of) {
+            (Some(snapshot), _) => snapshot.list_documents(collection_name, &ScanOptions::default()).map_err(|e| self.convert_document_error(e))?,
+            (None, Some(as_of)) => manager
+                .list_documents_as_of(collection_name, &ScanOptions::default(), as_of_timestamp(as_of)?)
+                .map_err(|e| self.convert_document_error(e))?,
+            (None, None) => {
+                let doc_ids = manager.list_document_ids(collection_name).map_err(|e| self.convert_document_error(e))?;
+                let mut documents = Vec::with_capacity(doc_ids.len());
+                for doc_id in doc_ids {
+                    if let Some(content) = manager.get_value(collection_name, &doc_id).map_err(|e| self.convert_document_error(e))? {
+                        documents.push((doc_id, content));
+                    }
+                }
+                documents
+            }
+        };
+
+        let mut matching_docs = Vec::new();
+        let query_lower = query.to_lowercase();
+
+        for (doc_id, content) in documents {
+            // Simple text search in document content
+            if let Ok(content_str) = serde_json::to_string(&content)
+                && content_str.to_lowercase().contains(&query_lower)
+            {
+                let (content, missing_fields) = project(doc_id.clone(), content, read.fields.as_ref());
+                matching_docs.push(Document {
+                    id: doc_id.to_string(),
+                    content,
+                    missing_fields,
+                    created_at: Utc::now(),
+                    updated_at: Utc::now(),
+                    version: 1,
+                });
+            }
+        }
+
+        let total_matches = matching_docs.len() as u64;
+
+        // Apply pagination
+        if let Some(offset) = offset {
+            matching_docs = matching_docs.into_iter().skip(offset as usize).collect();
+        }
+
+        if let Some(limit) = limit {
+            matching_docs.truncate(limit as usize);
+        }
+
+        let execution_time = start_time.elapsed();
+
+        info!("Search found {} matches in collection: {} (query: {})", total_matches, collection_name, query);
+
+        Ok(SearchResults {
+            documents: matching_docs,
+            total_matches,
+            query: query.to_string(),
+            execution_time_ms: execution_time.as_millis() as u64,
+        })
+    }
+
+    /// Group and aggregate the documents of a collection as `spec` describes, through
+    /// `snapshot` if given
+    pub async fn aggregate(&self, collection_name: &str, spec: &AggregationSpec, snapshot: Option<&ReadSnapshot>) -> ApiResult<AggregationResult> {
+        let result = match snapshot {
+            Some(snapshot) => snapshot.aggregate(collection_name, spec),
+            None => {
+                let manager = self.collection_manager.lock().await;
+
+                if !manager.collection_exists(collection_name).map_err(|e| self.convert_document_error(e))? {
+                    return Err(ApiError::NotFound {
+                        message: format!("Collection '{}' not found", collection_name),
+                    });
+                }
+
+                manager.aggregate(collection_name, spec)
+            }
+        }
+        .map_err(|e| self.convert_document_error(e))?;
+        info!(
+            "Aggregated {} documents of collection {} into {} groups",
+            result.documents_aggregated,
+            collection_name,
+            result.groups.len()
+        );
+        Ok(result)
+    }
+
+    /// Health check for database connection
+    pub async fn health_check(&self) -> ApiResult<bool> {
+        // Try to access the collection manager
+        let _manager = self.collection_manager.lock().await;
+        Ok(true)
+    }
+
+    /// Convert DotDB DocumentError to ApiError
+    fn convert_document_error(&self, error: DocumentError) -> ApiError {
+        match error {
+            DocumentError::DocumentNotFound(id) => ApiError::NotFound {
+                message: format!("Document not found: {}", id.0),
+            },
+            DocumentError::DocumentAlreadyExists(id) => ApiError::Conflict {
+                message: format!("Document already exists: {}", id.0),
+            },
+            error @ DocumentError::VersionConflict { current, .. } => ApiError::VersionConflict {
+                message: error.to_string(),
+                current_version: current,
+            },
+            DocumentError::CollectionNotFound(name) => ApiError::NotFound {
+                message: format!("Collection not found: {}", name.0),
+            },
+            error @ DocumentError::CollectionAlreadyExists(_) => ApiError::Conflict { message: error.to_string() },
+            DocumentError::InvalidDocumentId(id) => ApiError::BadRequest {
+                message: format!("Invalid document ID: {}", id),
+            },
+            DocumentError::InvalidCollectionName(name) => ApiError::BadRequest {
+                message: format!("Invalid collection name: {}", name),
+            },
+            DocumentError::InvalidNamespace(name) => ApiError::BadRequest {
+                message: format!("Invalid namespace: {}", name),
+            },
+            error @ DocumentError::NamespaceNotEmpty(_) => ApiError::Conflict { message: error.to_string() },
+            DocumentError::JsonSerialization(e) => ApiError::InternalServerError {
+                message: format!("JSON serialization error: {}", e),
+            },
+            DocumentError::Database(e) => ApiError::InternalServerError {
+                message: format!("Database error: {}", e),
+            },
+            DocumentError::Index(e) => ApiError::InternalServerError {
+                message: format!("Index error: {}", e),
+            },
+            DocumentError::Storage(e) => ApiError::InternalServerError {
+                message: format!("Storage error: {}", e),
+            },
+            DocumentError::Statistics(e) => ApiError::InternalServerError {
+                message: format!("Statistics error: {}", e),
+            },
+            DocumentError::InvalidSchema(message) => ApiError::BadRequest {
+                message: format!("Invalid schema: {}", message),
+            },
+            DocumentError::InvalidProjection(message) => ApiError::BadRequest {
+                message: format!("Invalid fields: {}", message),
+            },
+            error @ (DocumentError::InvalidAggregation(_) | DocumentError::InvalidCodec(_)) => ApiError::BadRequest { message: error.to_string() },
+            error @ DocumentError::SchemaViolation { .. } => ApiError::UnprocessableEntity { message: error.to_string() },
+            DocumentError::TransactionAborted { reason, .. } => abort_error(&reason),
+            DocumentError::InvalidSnapshot(message) => ApiError::BadRequest {
+                message: format!("Invalid snapshot: {}", message),
+            },
+            error @ DocumentError::ReadOnlyReplica { .. } => ApiError::Forbidden { message: error.to_string() },
+            error @ DocumentError::ReplicaBehind { .. } => ApiError::ServiceUnavailable { message: error.to_string() },
+            DocumentError::HistoryUnavailable { requested, horizon } => ApiError::NotFound {
+                message: format!(
+                    "History is only kept from {}; cannot read as of {}",
+                    DateTime::from_timestamp_nanos(horizon as i64),
+                    DateTime::from_timestamp_nanos(requested as i64)
+                ),
+            },
+            error @ DocumentError::SnapshotReleased { .. } => ApiError::NotFound { message: error.to_string() },
+            error @ (DocumentError::SnapshotIo(_) | DocumentError::Compression(_) | DocumentError::ChangeFeedTruncated { .. } | DocumentError::ReplicationGap { .. }) => {
+                ApiError::InternalServerError { message: error.to_string() }
+            }
+        }
+    }
+}
+
+/// Parse a document ID taken from a request path
+fn parse_document_id(document_id: &str) -> ApiResult<DocumentId> {
+    DocumentId::from_string(document_id).map_err(|_| ApiError::BadRequest {
+        message: format!("Invalid document ID: {}", document_id),
+    })
+}
+
+/// Nanoseconds since the Unix epoch of an `as_of` time, which DotDB reads history by
+fn as_of_timestamp(as_of: DateTime<Utc>) -> ApiResult<u64> {
+    as_of.timestamp_nanos_opt().and_then(|nanos| u64::try_from(nanos).ok()).ok_or_else(|| ApiError::BadRequest {
+        message: format!("as_of is out of range: {}", as_of.to_rfc3339()),
+    })
+}
+
+/// A document's content reduced to the projected paths, with the paths it does not have
+fn project(id: DocumentId, content: Value, fields: Option<&Projection>) -> (Value, Vec<String>) {
+    let Some(fields) = fields else {
+        return (content, Vec::new());
+    };
+
+    let projected = ProjectedDocument {
+        id,
+        fields: fields.paths().map(str::to_string).zip(fields.project(&content)).collect(),
+    };
+    let missing = projected.missing().into_iter().map(str::to_string).collect();
+    (projected.to_value(), missing)
+}
@@ -0,0 +1,530 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Batched state writes from the gateway to the runtime
+//!
+//! A [`WritePipeline`] buffers the state writes of one logical request, such as a
+//! dot execution, into an ordered batch instead of making a round trip per write.
+//! The batch goes to the runtime as one `ApplyWriteBatch` call once it holds
+//! [`PipelineConfig::max_writes`] writes or [`PipelineConfig::max_bytes`] bytes,
+//! and whatever is left goes when the request commits. The runtime applies each
+//! batch atomically and in order.
+//!
+//! Reads through the pipeline see its buffered writes before asking the backend,
+//! so a request always reads its own writes. Writes are numbered in the order the
+//! request made them; when a batch is refused, the error names the write that
+//! failed by that number, and none of the batch is applied.
+
+use crate::error::{ApiError, ApiResult};
+use crate::grpc_pool::{ChannelPool, Idempotency, RuntimeInterceptor};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use thiserror::Error;
+use tonic::service::interceptor::InterceptedService;
+use tonic::transport::Channel;
+use tracing::{debug, warn};
+
+mod proto {
+    tonic::include_proto!("database_service");
+}
+
+use proto::database_service_client::DatabaseServiceClient;
+
+/// gRPC client that presents the gateway's API key and forwards the current request's trace context
+fn db_client(channel: Channel, interceptor: RuntimeInterceptor) -> DatabaseServiceClient<InterceptedService<Channel, RuntimeInterceptor>> {
+    DatabaseServiceClient::with_interceptor(channel, interceptor)
+}
+
+/// A single state write
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateWrite {
+    Put { collection: String, key: String, value: Vec<u8> },
+    Delete { collection: String, key: String },
+}
+
+impl StateWrite {
+    pub fn collection(&self) -> &str {
+        match self {
+            StateWrite::Put { collection, .. } | StateWrite::Delete { collection, .. } => collection,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        match self {
+            StateWrite::Put { key, .. } | StateWrite::Delete { key, .. } => key,
+        }
+    }
+
+    /// Bytes the write adds to a batch
+    pub fn size(&self) -> usize {
+        let value = match self {
+            StateWrite::Put { value, .. } => value.len(),
+            StateWrite::Delete { .. } => 0,
+        };
+        self.collection().len() + self.key().len() + value
+    }
+
+    fn to_proto(&self) -> proto::StateWrite {
+        match self {
+            StateWrite::Put { collection, key, value } => proto::StateWrite {
+                collection: collection.clone(),
+                key: key.clone(),
+                value: value.clone(),
+                delete: false,
+            },
+            StateWrite::Delete { collection, key } => proto::StateWrite {
+                collection: collection.clone(),
+                key: key.clone(),
+                value: vec![],
+                delete: true,
+            },
+        }
+    }
+}
+
+impl fmt::Display for StateWrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operation = match self {
+            StateWrite::Put { .. } => "put",
+            StateWrite::Delete { .. } => "delete",
+        };
+        write!(f, "{} {}/{}", operation, self.collection(), self.key())
+    }
+}
+
+/// When a pipeline sends its buffered writes
+#[derive(Debug, Clone)]
+pub struct PipelineConfig {
+    /// Writes per batch
+    pub max_writes: usize,
+    /// Bytes of keys and values per batch; a single larger write is sent on its own
+    pub max_bytes: usize,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        Self {
+            max_writes: 128,
+            max_bytes: 256 * 1024,
+        }
+    }
+}
+
+/// Why a backend did not apply a batch
+#[derive(Debug, Error)]
+pub enum BatchError {
+    /// The write at `index` in the batch could not be applied, so none of the batch was
+    #[error("write {index} of the batch was refused: {message}")]
+    Refused { index: usize, message: String },
+    /// The batch did not reach the runtime, or its outcome is unknown
+    #[error(transparent)]
+    Failed(#[from] ApiError),
+}
+
+/// Where a pipeline reads state and applies its batches
+#[async_trait]
+pub trait StateBackend: Send + Sync {
+    /// Current value of `key`, `None` when it is not set
+    async fn get(&self, collection: &str, key: &str) -> ApiResult<Option<Vec<u8>>>;
+
+    /// Apply `writes` in order, all of them or none
+    async fn apply_batch(&self, request_id: &str, writes: &[StateWrite]) -> Result<(), BatchError>;
+}
+
+/// A state write of the request that could not be applied
+#[derive(Debug, Error)]
+pub enum PipelineError {
+    /// The runtime refused write `sequence`; the batch it was sent with, writes `batch.0` to
+    /// `batch.1`, was not applied
+    #[error("State write #{sequence} ({write}) failed: {message}; writes #{}-#{} were not applied", batch.0, batch.1)]
+    WriteRefused { sequence: u64, write: StateWrite, message: String, batch: (u64, u64) },
+    /// Writes `first` to `last` did not reach the runtime
+    #[error("State writes #{first}-#{last} were not applied: {source}")]
+    BatchFailed { first: u64, last: u64, source: ApiError },
+    #[error(transparent)]
+    Read(#[from] ApiError),
+}
+
+impl From<PipelineError> for ApiError {
+    fn from(error: PipelineError) -> Self {
+        match error {
+            PipelineError::WriteRefused { .. } => ApiError::UnprocessableEntity { message: error.to_string() },
+            PipelineError::BatchFailed { .. } => ApiError::ServiceUnavailable { message: error.to_string() },
+            PipelineError::Read(error) => error,
+        }
+    }
+}
+
+/// How a pipeline's reads and writes were served
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PipelineStats {
+    /// Writes the request made
+    pub writes: u64,
+    /// Batches sent, each one round trip
+    pub batches: u64,
+    /// Reads answered from buffered writes
+    pub buffered_reads: u64,
+    /// Reads that went to the backend
+    pub backend_reads: u64,
+}
+
+/// The state writes of one logical request, buffered into ordered batches
+pub struct WritePipeline {
+    backend: Arc<dyn StateBackend>,
+    config: PipelineConfig,
+    request_id: String,
+    /// Buffered writes and their sequence numbers, in the order they were made
+    pending: Vec<(u64, StateWrite)>,
+    pending_bytes: usize,
+    /// Latest buffered value of each key written since the last batch; `None` is a delete
+    overlay: HashMap<(String, String), Option<Vec<u8>>>,
+    next_sequence: u64,
+    stats: PipelineStats,
+}
+
+impl WritePipeline {
+    pub fn new(backend: Arc<dyn StateBackend>, request_id: impl Into<String>, config: PipelineConfig) -> Self {
+        Self {
+            backend,
+            config,
+            request_id: request_id.into(),
+            pending: Vec::new(),
+            pending_bytes: 0,
+            overlay: HashMap::new(),
+            next_sequence: 1,
+            stats: PipelineStats::default(),
+        }
+    }
+
+    /// Set `key`, returning the write's sequence number
+    pub async fn put(&mut self, collection: &str, key: &str, value: Vec<u8>) -> Result<u64, PipelineError> {
+        self.write(StateWrite::Put {
+            collection: collection.to_string(),
+            key: key.to_string(),
+            value,
+        })
+        .await
+    }
+
+    /// Remove `key`, returning the write's sequence number
+    pub async fn delete(&mut self, collection: &str, key: &str) -> Result<u64, PipelineError> {
+        self.write(StateWrite::Delete {
+            collection: collection.to_string(),
+            key: key.to_string(),
+        })
+        .await
+    }
+
+    /// Buffer `write`, sending the batch once it is full
+    pub async fn write(&mut self, write: StateWrite) -> Result<u64, PipelineError> {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.stats.writes += 1;
+
+        let value = match &write {
+            StateWrite::Put { value, .. } => Some(value.clone()),
+            StateWrite::Delete { .. } => None,
+        };
+        self.overlay.insert((write.collection().to_string(), write.key().to_string()), value);
+        self.pending_bytes += write.size();
+        self.pending.push((sequence, write));
+
+        if self.pending.len() >= self.config.max_writes || self.pending_bytes >= self.config.max_bytes {
+            self.flush().await?;
+        }
+        Ok(sequence)
+    }
+
+    /// Value of `key` as this request last wrote it, or else as the backend holds it
+    pub async fn get(&mut self, collection: &str, key: &str) -> Result<Option<Vec<u8>>, PipelineError> {
+        if let Some(value) = self.overlay.get(&(collection.to_string(), key.to_string())) {
+            self.stats.buffered_reads += 1;
+            return Ok(value.clone());
+        }
+        self.stats.backend_reads += 1;
+        Ok(self.backend.get(collection, key).await?)
+    }
+
+    /// Send the buffered writes as one batch. A refused or failed batch is dropped.
+    pub async fn flush(&mut self) -> Result<(), PipelineError> {
+        let Some(&(first, _)) = self.pending.first() else {
+            return Ok(());
+        };
+        let (sequences, writes): (Vec<u64>, Vec<StateWrite>) = std::mem::take(&mut self.pending).into_iter().unzip();
+        let last = sequences[sequences.len() - 1];
+        self.pending_bytes = 0;
+        self.overlay.clear();
+        self.stats.batches += 1;
+
+        debug!(request_id = %self.request_id, writes = writes.len(), first, last, "Sending state write batch");
+        match self.backend.apply_batch(&self.request_id, &writes).await {
+            Ok(()) => Ok(()),
+            Err(BatchError::Refused { index, message }) if index < writes.len() => {
+                warn!(request_id = %self.request_id, sequence = sequences[index], %message, "State write refused");
+                Err(PipelineError::WriteRefused {
+                    sequence: sequences[index],
+                    write: writes[index].clone(),
+                    message,
+                    batch: (first, last),
+                })
+            }
+            Err(BatchError::Refused { index, message }) => Err(PipelineError::BatchFailed {
+                first,
+                last,
+                source: ApiError::InternalServerError {
+                    message: format!("Runtime refused write {} of a batch of {}: {}", index, writes.len(), message),
+                },
+            }),
+            Err(BatchError::Failed(source)) => Err(PipelineError::BatchFailed { first, last, source }),
+        }
+    }
+
+    /// Send what is still buffered; the request's writes are all applied once this succeeds
+    pub async fn commit(mut self) -> Result<PipelineStats, PipelineError> {
+        self.flush().await?;
+        Ok(self.stats)
+    }
+
+    /// Writes buffered and not yet sent
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn stats(&self) -> PipelineStats {
+        self.stats
+    }
+}
+
+/// State held by the runtime's database service, reached through pooled channels
+#[derive(Clone)]
+pub struct RuntimeStateBackend {
+    pool: Arc<ChannelPool>,
+}
+
+impl RuntimeStateBackend {
+    pub fn new(pool: Arc<ChannelPool>) -> Self {
+        Self { pool }
+    }
+
+    /// Whether the database service reports itself serving
+    pub async fn health_check(&self) -> ApiResult<bool> {
+        let request = proto::HealthCheckRequest {
+            services: vec![],
+            include_details: false,
+        };
+        let response = db_client(self.pool.channel(), self.pool.interceptor())
+            .health_check(request)
+            .await
+            .map_err(|e| ApiError::ServiceUnavailable {
+                message: format!("Database service health check failed: {}", e),
+            })?;
+        Ok(response.into_inner().overall_status == proto::OverallHealth::HealthServing as i32)
+    }
+}
+
+#[async_trait]
+impl StateBackend for RuntimeStateBackend {
+    async fn get(&self, collection: &str, key: &str) -> ApiResult<Option<Vec<u8>>> {
+        let request = proto::GetRequest {
+            collection: collection.to_string(),
+            key: key.to_string(),
+            transaction_id: String::new(),
+        };
+        let response = self
+            .pool
+            .call(Idempotency::Idempotent, |channel| {
+                let request = request.clone();
+                async move { db_client(channel, self.pool.interceptor()).get(request).await }
+            })
+            .await
+            .map_err(|e| ApiError::InternalServerError {
+                message: format!("gRPC call failed: {}", e),
+            })?
+            .into_inner();
+
+        // The runtime reports a missing key or collection as an unsuccessful read
+        Ok(response.success.then_some(response.value))
+    }
+
+    async fn apply_batch(&self, request_id: &str, writes: &[StateWrite]) -> Result<(), BatchError> {
+        let request = proto::WriteBatchRequest {
+            writes: writes.iter().map(StateWrite::to_proto).collect(),
+            request_id: request_id.to_string(),
+        };
+        // Never retried: a batch whose response was lost may already have been applied
+        let response = self
+            .pool
+            .call(Idempotency::NonIdempotent, |channel| {
+                let request = request.clone();
+                async move { db_client(channel, self.pool.interceptor()).apply_write_batch(request).await }
+            })
+            .await
+            .map_err(|e| ApiError::InternalServerError {
+                message: format!("gRPC call failed: {}", e),
+            })?
+            .into_inner();
+
+        if response.success {
+            Ok(())
+        } else {
+            Err(BatchError::Refused {
+                index: response.failed_index as usize,
+                message: response.error_message,
+            })
+        }
+    }
+}
+
+/// State kept in gateway memory, for running without a runtime and for tests
+#[derive(Default)]
+pub struct MemoryStateBackend {
+    state: Mutex<HashMap<(String, String), Vec<u8>>>,
+    round_trips: AtomicU64,
+}
+
+impl MemoryStateBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Calls made to the backend so far, reads and batches alike
+    pub fn round_trips(&self) -> u64 {
+        self.round_trips.load(Ordering::Relaxed)
+    }
+}
+
+#[async_trait]
+impl StateBackend for MemoryStateBackend {
+    async fn get(&self, collection: &str, key: &str) -> ApiResult<Option<Vec<u8>>> {
+        self.round_trips.fetch_add(1, Ordering::Relaxed);
+        Ok(self.state.lock().get(&(collection.to_string(), key.to_string())).cloned())
+    }
+
+    async fn apply_batch(&self, _request_id: &str, writes: &[StateWrite]) -> Result<(), BatchError> {
+        self.round_trips.fetch_add(1, Ordering::Relaxed);
+        let mut state = self.state.lock();
+
+        // Applied to a copy first, so a refused write leaves the state as it was
+        let mut staged = state.clone();
+        for (index, write) in writes.iter().enumerate() {
+            let slot = (write.collection().to_string(), write.key().to_string());
+            match write {
+                StateWrite::Put { value, .. } => {
+                    staged.insert(slot, value.clone());
+                }
+                StateWrite::Delete { collection, key } => {
+                    if staged.remove(&slot).is_none() {
+                        return Err(BatchError::Refused {
+                            index,
+                            message: format!("Key '{}' not found in collection '{}'", key, collection),
+                        });
+                    }
+                }
+            }
+        }
+        *state = staged;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pipeline(backend: &Arc<MemoryStateBackend>, max_writes: usize) -> WritePipeline {
+        let config = PipelineConfig {
+            max_writes,
+            ..PipelineConfig::default()
+        };
+        WritePipeline::new(backend.clone(), "req-1", config)
+    }
+
+    #[tokio::test]
+    async fn test_writes_are_batched_until_full_or_committed() {
+        let backend = Arc::new(MemoryStateBackend::new());
+        let mut writes = pipeline(&backend, 4);
+
+        for i in 0..10 {
+            writes.put("counters", &format!("k{}", i), vec![i]).await.unwrap();
+        }
+        // Two full batches went out; the last two writes wait for the commit
+        assert_eq!((backend.round_trips(), writes.pending()), (2, 2));
+        assert_eq!(backend.get("counters", "k9").await.unwrap(), None);
+
+        let stats = writes.commit().await.unwrap();
+        assert_eq!((stats.writes, stats.batches), (10, 3));
+        assert_eq!(backend.get("counters", "k9").await.unwrap(), Some(vec![9]));
+    }
+
+    #[tokio::test]
+    async fn test_byte_threshold_sends_the_batch() {
+        let backend = Arc::new(MemoryStateBackend::new());
+        let config = PipelineConfig { max_writes: 100, max_bytes: 64 };
+        let mut writes = WritePipeline::new(backend.clone(), "req-1", config);
+
+        writes.put("blobs", "small", vec![0; 8]).await.unwrap();
+        assert_eq!(writes.pending(), 1);
+        writes.put("blobs", "large", vec![0; 64]).await.unwrap();
+        assert_eq!((backend.round_trips(), writes.pending()), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn test_reads_see_the_requests_own_writes() {
+        let backend = Arc::new(MemoryStateBackend::new());
+        let mut setup = pipeline(&backend, 100);
+        setup.put("counters", "visits", b"1".to_vec()).await.unwrap();
+        setup.put("counters", "stale", b"x".to_vec()).await.unwrap();
+        setup.commit().await.unwrap();
+
+        let mut writes = pipeline(&backend, 100);
+        writes.put("counters", "visits", b"2".to_vec()).await.unwrap();
+        writes.delete("counters", "stale").await.unwrap();
+
+        assert_eq!(writes.get("counters", "visits").await.unwrap(), Some(b"2".to_vec()));
+        assert_eq!(writes.get("counters", "stale").await.unwrap(), None);
+        assert_eq!(writes.get("counters", "other").await.unwrap(), None);
+        assert_eq!((writes.stats().buffered_reads, writes.stats().backend_reads), (2, 1));
+
+        // Once sent, the writes are read back from the backend
+        writes.flush().await.unwrap();
+        assert_eq!(writes.get("counters", "visits").await.unwrap(), Some(b"2".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_refused_batch_names_the_failed_write() {
+        let backend = Arc::new(MemoryStateBackend::new());
+        let mut writes = pipeline(&backend, 100);
+
+        writes.put("counters", "a", vec![1]).await.unwrap();
+        writes.put("counters", "b", vec![1]).await.unwrap();
+        writes.delete("counters", "missing").await.unwrap();
+        writes.put("counters", "c", vec![1]).await.unwrap();
+
+        let error = writes.commit().await.unwrap_err();
+        assert!(matches!(&error, PipelineError::WriteRefused { sequence: 3, batch: (1, 4), .. }));
+        assert_eq!(
+            error.to_string(),
+            "State write #3 (delete counters/missing) failed: Key 'missing' not found in collection 'counters'; writes #1-#4 were not applied"
+        );
+        assert!(matches!(ApiError::from(error), ApiError::UnprocessableEntity { .. }));
+        // The batch was applied all or nothing
+        assert_eq!(backend.get("counters", "a").await.unwrap(), None);
+    }
+}
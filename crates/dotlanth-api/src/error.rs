@@ -18,6 +18,8 @@
 //! Implements RFC 7807 Problem Details format
 
 use http_body_util::Full;
+use crate::models::FieldError;
+use hyper::header::{ETAG, RETRY_AFTER};
 use hyper::{Response, StatusCode, body::Bytes};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -45,12 +47,36 @@ pub enum ApiError {
     #[error("Conflict: {message}")]
     Conflict { message: String },
 
+    #[error("Gone: {message}")]
+    Gone { message: String, migration_guide: String },
+
+    /// A conditional write found the document at another version; `current_version` is sent
+    /// as its `ETag`
+    #[error("Precondition failed: {message}")]
+    VersionConflict { message: String, current_version: u64 },
+
+    /// A document transaction was aborted; `code` tells clients why and `retryable`
+    /// whether running it again may succeed
+    #[error("Transaction aborted: {message}")]
+    TransactionAborted { message: String, code: &'static str, retryable: bool },
+
+    #[error("Bad request: input does not match the ABI of dot '{dot_id}' ({} invalid fields)", errors.len())]
+    InputValidation { dot_id: String, errors: Vec<FieldError> },
+
+    #[error("Payload too large: {message}")]
+    PayloadTooLarge { message: String },
+
     #[error("Unprocessable entity: {message}")]
     UnprocessableEntity { message: String },
 
     #[error("Too many requests: {message}")]
     TooManyRequests { message: String },
 
+    /// An execution was shed by admission control; `reason` tells clients why and
+    /// `retry_after_secs` is sent as `Retry-After`
+    #[error("Too many requests: {message}")]
+    Overloaded { message: String, reason: &'static str, retry_after_secs: u64 },
+
     #[error("Internal server error: {message}")]
     InternalServerError { message: String },
 
@@ -92,8 +118,14 @@ impl ApiError {
             ApiError::NotFound { .. } => StatusCode::NOT_FOUND,
             ApiError::MethodNotAllowed { .. } => StatusCode::METHOD_NOT_ALLOWED,
             ApiError::Conflict { .. } => StatusCode::CONFLICT,
+            ApiError::Gone { .. } => StatusCode::GONE,
+            ApiError::VersionConflict { .. } => StatusCode::PRECONDITION_FAILED,
+            ApiError::TransactionAborted { .. } => StatusCode::CONFLICT,
+            ApiError::InputValidation { .. } => StatusCode::BAD_REQUEST,
+            ApiError::PayloadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
             ApiError::UnprocessableEntity { .. } => StatusCode::UNPROCESSABLE_ENTITY,
             ApiError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Overloaded { .. } => StatusCode::TOO_MANY_REQUESTS,
             ApiError::InternalServerError { .. } => StatusCode::INTERNAL_SERVER_ERROR,
             ApiError::ServiceUnavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
             ApiError::GatewayTimeout { .. } => StatusCode::GATEWAY_TIMEOUT,
@@ -123,8 +155,14 @@ impl ApiError {
             ApiError::NotFound { .. } => "not_found",
             ApiError::MethodNotAllowed { .. } => "method_not_allowed",
             ApiError::Conflict { .. } => "conflict",
+            ApiError::Gone { .. } => "gone",
+            ApiError::VersionConflict { .. } => "version_conflict",
+            ApiError::TransactionAborted { .. } => "transaction_aborted",
+            ApiError::InputValidation { .. } => "input_validation",
+            ApiError::PayloadTooLarge { .. } => "payload_too_large",
             ApiError::UnprocessableEntity { .. } => "unprocessable_entity",
             ApiError::TooManyRequests { .. } => "too_many_requests",
+            ApiError::Overloaded { .. } => "overloaded",
             ApiError::InternalServerError { .. } => "internal_server_error",
             ApiError::ServiceUnavailable { .. } => "service_unavailable",
             ApiError::GatewayTimeout { .. } => "gateway_timeout",
@@ -194,6 +232,9 @@ impl ProblemDetails {
             StatusCode::NOT_FOUND => "Not Found".to_string(),
             StatusCode::METHOD_NOT_ALLOWED => "Method Not Allowed".to_string(),
             StatusCode::CONFLICT => "Conflict".to_string(),
+            StatusCode::GONE => "Gone".to_string(),
+            StatusCode::PRECONDITION_FAILED => "Precondition Failed".to_string(),
+            StatusCode::PAYLOAD_TOO_LARGE => "Payload Too Large".to_string(),
             StatusCode::UNPROCESSABLE_ENTITY => "Unprocessable Entity".to_string(),
             StatusCode::TOO_MANY_REQUESTS => "Too Many Requests".to_string(),
             StatusCode::INTERNAL_SERVER_ERROR => "Internal Server Error".to_string(),
@@ -208,7 +249,29 @@ impl ProblemDetails {
 impl From<ApiError> for Response<Full<Bytes>> {
     fn from(error: ApiError) -> Self {
         let status_code = error.status_code();
-        let problem_details = ProblemDetails::new(&error, "/".to_string());
+        let mut problem_details = ProblemDetails::new(&error, "/".to_string());
+        match &error {
+            ApiError::InputValidation { errors, .. } => {
+                problem_details = problem_details.with_extension("errors".to_string(), serde_json::to_value(errors).unwrap_or_default());
+            }
+            ApiError::Gone { migration_guide, .. } => {
+                problem_details = problem_details.with_extension("migration_guide".to_string(), serde_json::Value::String(migration_guide.clone()));
+            }
+            ApiError::TransactionAborted { code, retryable, .. } => {
+                problem_details = problem_details
+                    .with_extension("code".to_string(), serde_json::Value::String(code.to_string()))
+                    .with_extension("retryable".to_string(), serde_json::Value::Bool(*retryable));
+            }
+            ApiError::VersionConflict { current_version, .. } => {
+                problem_details = problem_details.with_extension("current_version".to_string(), serde_json::Value::from(*current_version));
+            }
+            ApiError::Overloaded { reason, retry_after_secs, .. } => {
+                problem_details = problem_details
+                    .with_extension("reason".to_string(), serde_json::Value::String(reason.to_string()))
+                    .with_extension("retry_after".to_string(), serde_json::Value::from(*retry_after_secs));
+            }
+            _ => {}
+        }
 
         // Log the error
         error!("API Error: {} - {}", status_code, error);
@@ -222,10 +285,16 @@ impl From<ApiError> for Response<Full<Bytes>> {
             }
         };
 
-        Response::builder()
+        let mut response = Response::builder()
             .status(status_code)
             .header("content-type", "application/problem+json")
-            .header("cache-control", "no-cache")
+            .header("cache-control", "no-cache");
+        match &error {
+            ApiError::Overloaded { retry_after_secs, .. } => response = response.header(RETRY_AFTER, *retry_after_secs),
+            ApiError::VersionConflict { current_version, .. } => response = response.header(ETAG, version_etag(*current_version)),
+            _ => {}
+        }
+        response
             .body(Full::new(Bytes::from(json)))
             .unwrap_or_else(|e| {
                 error!("Failed to build error response: {}", e);
@@ -237,6 +306,11 @@ impl From<ApiError> for Response<Full<Bytes>> {
     }
 }
 
+/// `ETag` of a document at `version`
+pub fn version_etag(version: u64) -> String {
+    format!("\"{version}\"")
+}
+
 /// Result type for API operations
 pub type ApiResult<T> = Result<T, ApiError>;
 
@@ -30,7 +30,7 @@ use hyper::{Method, Request, Response, StatusCode, Uri};
 use std::sync::Arc;
 use std::time::Instant;
 use tokio::sync::{Mutex, RwLock};
-use tonic::transport::{Channel, Endpoint};
+use tonic::transport::Channel;
 use tracing::{debug, error, info, warn};
 
 /// Authentication context for bridge operations
@@ -338,11 +338,7 @@ impl Bridge {
 
         // Create new channel
         let endpoint_url = self.get_service_endpoint(service)?;
-        let endpoint = Endpoint::from_shared(endpoint_url)
-            .map_err(|e| ApiError::InternalServerError {
-                message: format!("Invalid gRPC endpoint: {}", e),
-            })?
-            .timeout(std::time::Duration::from_millis(self.config.max_timeout_ms));
+        let endpoint = self.config.tls.endpoint(&endpoint_url)?.timeout(std::time::Duration::from_millis(self.config.max_timeout_ms));
 
         let channel = endpoint.connect().await.map_err(|e| ApiError::ServiceUnavailable {
             message: format!("Failed to connect to gRPC service '{}': {}", service, e),
@@ -377,8 +373,10 @@ impl Bridge {
             ApiError::Forbidden { .. } => StatusCode::FORBIDDEN,
             ApiError::NotFound { .. } => StatusCode::NOT_FOUND,
             ApiError::Conflict { .. } => StatusCode::CONFLICT,
+            ApiError::VersionConflict { .. } => StatusCode::PRECONDITION_FAILED,
+            ApiError::PayloadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
             ApiError::UnprocessableEntity { .. } => StatusCode::UNPROCESSABLE_ENTITY,
-            ApiError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::TooManyRequests { .. } | ApiError::Overloaded { .. } => StatusCode::TOO_MANY_REQUESTS,
             ApiError::ServiceUnavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
             ApiError::GatewayTimeout { .. } => StatusCode::GATEWAY_TIMEOUT,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
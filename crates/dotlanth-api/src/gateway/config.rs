@@ -18,6 +18,7 @@
 
 use super::bridge::Bridge;
 use crate::error::{ApiError, ApiResult};
+use crate::grpc_pool::GrpcTlsConfig;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::info;
@@ -35,6 +36,8 @@ pub struct GatewayConfig {
     pub max_streaming_connections: usize,
     /// Buffer size for streaming operations
     pub streaming_buffer_size: usize,
+    /// TLS for the bridge's gRPC channels
+    pub tls: GrpcTlsConfig,
 }
 
 impl Default for GatewayConfig {
@@ -45,6 +48,7 @@ impl Default for GatewayConfig {
             enable_metrics: true,
             max_streaming_connections: 1000,
             streaming_buffer_size: 8192,
+            tls: GrpcTlsConfig::default(),
         }
     }
 }
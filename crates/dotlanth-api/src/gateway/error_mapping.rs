@@ -86,9 +86,15 @@ impl ErrorMapper {
             ApiError::Forbidden { message } => Status::permission_denied(message),
             ApiError::NotFound { message } => Status::not_found(message),
             ApiError::Conflict { message } => Status::already_exists(message),
+            ApiError::Gone { message, .. } => Status::not_found(message),
+            ApiError::VersionConflict { message, .. } => Status::failed_precondition(message),
+            ApiError::TransactionAborted { message, .. } => Status::aborted(message),
+            ApiError::InputValidation { .. } => Status::invalid_argument(error.to_string()),
             ApiError::MethodNotAllowed { message } => Status::invalid_argument(message),
+            ApiError::PayloadTooLarge { message } => Status::resource_exhausted(message),
             ApiError::UnprocessableEntity { message } => Status::invalid_argument(message),
             ApiError::TooManyRequests { message } => Status::resource_exhausted(message),
+            ApiError::Overloaded { message, .. } => Status::resource_exhausted(message),
             ApiError::ServiceUnavailable { message } => Status::unavailable(message),
             ApiError::GatewayTimeout { message } => Status::deadline_exceeded(message),
             ApiError::InternalServerError { message } => Status::internal(message),
@@ -160,8 +166,14 @@ impl ErrorMapper {
             ApiError::NotFound { .. } => "not_found",
             ApiError::MethodNotAllowed { .. } => "method_not_allowed",
             ApiError::Conflict { .. } => "conflict",
+            ApiError::Gone { .. } => "gone",
+            ApiError::VersionConflict { .. } => "version_conflict",
+            ApiError::TransactionAborted { .. } => "transaction_aborted",
+            ApiError::InputValidation { .. } => "input_validation",
+            ApiError::PayloadTooLarge { .. } => "payload_too_large",
             ApiError::UnprocessableEntity { .. } => "unprocessable_entity",
             ApiError::TooManyRequests { .. } => "too_many_requests",
+            ApiError::Overloaded { .. } => "overloaded",
             ApiError::ServiceUnavailable { .. } => "service_unavailable",
             ApiError::GatewayTimeout { .. } => "gateway_timeout",
             ApiError::InternalServerError { .. } => "internal_server_error",
@@ -9,5 +9,6 @@ pub mod query;
 pub mod schema;
 pub mod subscription;
 pub mod types;
+pub mod ws;
 
 pub use schema::{AppSchema, build_schema};
@@ -2,7 +2,7 @@
 // Copyright (C) 2025 Synerthink
 
 use super::types::{GqlApiVersion, GqlCollection, GqlDocument, GqlDocumentList, GqlSearchResults};
-use crate::db::DatabaseClient;
+use crate::db::{DatabaseClient, ReadOptions};
 use crate::models::SearchResults;
 use crate::vm::VmClient;
 use async_graphql::{Context, Object, Result as GqlResult};
@@ -26,15 +26,17 @@ impl QueryRoot {
         Ok(cols.into_iter().map(GqlCollection::from).collect())
     }
 
-    async fn documents(&self, ctx: &Context<'_>, collection: String, page: Option<u32>, page_size: Option<u32>) -> GqlResult<GqlDocumentList> {
+    async fn documents(&self, ctx: &Context<'_>, collection: String, page: Option<u32>, page_size: Option<u32>, sort: Option<String>, descending: Option<bool>) -> GqlResult<GqlDocumentList> {
         let db = ctx.data_unchecked::<DatabaseClient>().clone();
-        let list = db.get_documents(&collection, page.unwrap_or(1), page_size.unwrap_or(20)).await?;
+        let list = db
+            .get_documents(&collection, page.unwrap_or(1), page_size.unwrap_or(20), sort, descending.unwrap_or(false), &ReadOptions::default())
+            .await?;
         Ok(list.into())
     }
 
     async fn document(&self, ctx: &Context<'_>, collection: String, id: String) -> GqlResult<GqlDocument> {
         let db = ctx.data_unchecked::<DatabaseClient>().clone();
-        let d = db.get_document(&collection, &id).await?;
+        let d = db.get_document(&collection, &id, None).await?;
         Ok(d.into())
     }
 
@@ -45,7 +47,7 @@ impl QueryRoot {
 
     async fn search_documents(&self, ctx: &Context<'_>, collection: String, q: String, limit: Option<u32>, offset: Option<u32>) -> GqlResult<GqlSearchResults> {
         let db = ctx.data_unchecked::<DatabaseClient>().clone();
-        let r = db.search_documents(&collection, &q, limit, offset).await?;
+        let r = db.search_documents(&collection, &q, limit, offset, &ReadOptions::default()).await?;
         Ok(r.into())
     }
 
@@ -1,11 +1,14 @@
 // Dotlanth
 // Copyright (C) 2025 Synerthink
 
-use super::types::GqlWebSocketMessage as WebSocketMessage;
+use super::guards::ClaimsExt;
+use super::types::{GqlDotStateChange, GqlWebSocketMessage as WebSocketMessage, STATE_CHANGED_EVENT};
+use super::ws::SubscriptionLimiter;
+use crate::auth::Claims;
 use crate::vm::VmClient;
 use crate::websocket::WebSocketManager;
 use async_graphql::futures_util::stream::{Stream, StreamExt};
-use async_graphql::{Context, Result as GqlResult, Subscription};
+use async_graphql::{Context, ErrorExtensions, ID, Result as GqlResult, ServerError, Subscription};
 use std::pin::Pin;
 use std::sync::Arc;
 
@@ -37,4 +40,34 @@ impl SubscriptionRoot {
         let stream = tokio_stream::wrappers::UnboundedReceiverStream::new(rx).map(|m| m);
         Box::pin(stream)
     }
+
+    /// Emits the changed keys, version and state root of every committed state
+    /// transition of a dot.
+    ///
+    /// The upstream event stream is held open only while the subscription is
+    /// active; unsubscribing or dropping the connection cancels it.
+    async fn dot_state_changed(&self, ctx: &Context<'_>, dot_id: ID) -> GqlResult<Pin<Box<dyn Stream<Item = GqlResult<GqlDotStateChange>> + Send>>> {
+        let claims = ctx
+            .data_opt::<Claims>()
+            .ok_or_else(|| ServerError::new("Authentication required", None).extend_with(|_err, e| e.set("code", "UNAUTHENTICATED")))?;
+        claims.require_permissions(&["execute:dots"])?;
+
+        let permit = match ctx.data_opt::<SubscriptionLimiter>() {
+            Some(limiter) => Some(limiter.acquire()?),
+            None => None,
+        };
+
+        let events = self.vm.stream_dot_events(&dot_id, vec![STATE_CHANGED_EVENT.to_string()], vec![], None).await?;
+
+        let stream = events.filter_map(move |event| {
+            // Tie the permit to the stream so the slot is released on teardown
+            let _permit = &permit;
+            let change = match event {
+                Ok(event) => GqlDotStateChange::from_event(&event).map(Ok),
+                Err(e) => Some(Err(e.into())),
+            };
+            async move { change }
+        });
+        Ok(Box::pin(stream))
+    }
 }
@@ -2,7 +2,7 @@
 // Copyright (C) 2025 Synerthink
 
 use crate::models;
-use async_graphql::{InputObject, Json, SimpleObject};
+use async_graphql::{ID, InputObject, Json, SimpleObject};
 use chrono::{DateTime, Utc};
 
 #[derive(SimpleObject, Clone)]
@@ -136,6 +136,7 @@ pub struct GqlDeployDotResponse {
     pub validation_valid: bool,
     pub validation_errors: Vec<String>,
     pub validation_warnings: Vec<String>,
+    pub architecture: String,
 }
 
 impl From<models::DeployDotResponse> for GqlDeployDotResponse {
@@ -147,6 +148,7 @@ impl From<models::DeployDotResponse> for GqlDeployDotResponse {
             validation_valid: r.validation.valid,
             validation_errors: r.validation.errors,
             validation_warnings: r.validation.warnings,
+            architecture: r.architecture,
         }
     }
 }
@@ -192,6 +194,7 @@ impl From<GqlExecuteDotInput> for models::ExecuteDotRequest {
         Self {
             function: i.function,
             arguments: i.arguments.into_iter().map(|Json(v)| v).collect(),
+            inputs: Default::default(),
             context: i.context.map(Into::into),
         }
     }
@@ -253,3 +256,99 @@ impl From<models::SearchResults> for GqlSearchResults {
         }
     }
 }
+
+/// Event type the runtime reports for committed state transitions
+pub const STATE_CHANGED_EVENT: &str = "state_changed";
+
+#[derive(SimpleObject, Clone, Debug, PartialEq)]
+pub struct GqlDotStateChange {
+    pub dot_id: ID,
+    pub event_id: String,
+    pub changed_keys: Vec<String>,
+    pub version: u64,
+    pub state_root: String,
+}
+
+impl GqlDotStateChange {
+    /// Build a state change from a `state_changed` dot event.
+    ///
+    /// Fields are read from the event data, falling back to the event metadata for
+    /// `version` and `state_root`. Returns `None` for other event types or events
+    /// without a version.
+    pub fn from_event(event: &models::DotEvent) -> Option<Self> {
+        if event.event_type != STATE_CHANGED_EVENT {
+            return None;
+        }
+
+        let version = event
+            .data
+            .get("version")
+            .and_then(|v| v.as_u64())
+            .or_else(|| event.metadata.get("version").and_then(|v| v.parse().ok()))?;
+        let state_root = event
+            .data
+            .get("state_root")
+            .and_then(|v| v.as_str())
+            .or_else(|| event.metadata.get("state_root").map(String::as_str))
+            .unwrap_or_default()
+            .to_string();
+        let changed_keys = event
+            .data
+            .get("changed_keys")
+            .and_then(|v| v.as_array())
+            .map(|keys| keys.iter().filter_map(|k| k.as_str().map(str::to_string)).collect())
+            .unwrap_or_default();
+
+        Some(Self {
+            dot_id: ID(event.dot_id.clone()),
+            event_id: event.event_id.clone(),
+            changed_keys,
+            version,
+            state_root,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn event(event_type: &str, data: serde_json::Value, metadata: HashMap<String, String>) -> models::DotEvent {
+        models::DotEvent {
+            event_id: "evt-1".to_string(),
+            dot_id: "dot-1".to_string(),
+            event_type: event_type.to_string(),
+            data,
+            metadata,
+        }
+    }
+
+    #[test]
+    fn test_state_change_from_event_data() {
+        let data = serde_json::json!({ "version": 7, "state_root": "abc123", "changed_keys": ["balance", "owner"] });
+        let change = GqlDotStateChange::from_event(&event(STATE_CHANGED_EVENT, data, HashMap::new())).unwrap();
+
+        assert_eq!(change.dot_id, ID("dot-1".to_string()));
+        assert_eq!(change.version, 7);
+        assert_eq!(change.state_root, "abc123");
+        assert_eq!(change.changed_keys, vec!["balance".to_string(), "owner".to_string()]);
+    }
+
+    #[test]
+    fn test_state_change_falls_back_to_metadata() {
+        let metadata = HashMap::from([("version".to_string(), "3".to_string()), ("state_root".to_string(), "def456".to_string())]);
+        let change = GqlDotStateChange::from_event(&event(STATE_CHANGED_EVENT, serde_json::json!({}), metadata)).unwrap();
+
+        assert_eq!(change.version, 3);
+        assert_eq!(change.state_root, "def456");
+        assert!(change.changed_keys.is_empty());
+    }
+
+    #[test]
+    fn test_state_change_ignores_other_events() {
+        let data = serde_json::json!({ "version": 1 });
+        assert!(GqlDotStateChange::from_event(&event("execution_completed", data, HashMap::new())).is_none());
+        assert!(GqlDotStateChange::from_event(&event(STATE_CHANGED_EVENT, serde_json::json!({}), HashMap::new())).is_none());
+    }
+}
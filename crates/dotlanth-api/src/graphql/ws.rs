@@ -0,0 +1,264 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+use super::schema::AppSchema;
+use crate::auth::{AuthService, Claims, extract_token_from_header};
+use crate::error::ApiError;
+use crate::websocket::{WebSocketManager, shutdown_close_frame};
+use async_graphql::http::{WebSocket as GqlWebSocket, WebSocketProtocols, WsMessage};
+use async_graphql::{Data, ErrorExtensions, Result as GqlResult, ServerError};
+use base64::{Engine as _, engine::general_purpose};
+use futures::{SinkExt, StreamExt};
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use sha1::{Digest, Sha1};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::Mutex;
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::protocol::frame::CloseFrame;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tracing::{debug, error, info, warn};
+
+/// Maximum number of concurrent subscriptions on a single GraphQL WebSocket connection
+pub const MAX_SUBSCRIPTIONS_PER_CONNECTION: usize = 16;
+
+/// Counts the active subscriptions of one connection
+#[derive(Clone)]
+pub struct SubscriptionLimiter {
+    active: Arc<AtomicUsize>,
+    max: usize,
+}
+
+impl SubscriptionLimiter {
+    pub fn new(max: usize) -> Self {
+        Self {
+            active: Arc::new(AtomicUsize::new(0)),
+            max,
+        }
+    }
+
+    /// Reserve a subscription slot, released when the returned permit is dropped
+    pub fn acquire(&self) -> GqlResult<SubscriptionPermit> {
+        let reserved = self.active.fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| (n < self.max).then_some(n + 1));
+        match reserved {
+            Ok(_) => Ok(SubscriptionPermit { active: self.active.clone() }),
+            Err(_) => Err(ServerError::new(format!("Subscription limit of {} per connection reached", self.max), None)
+                .extend_with(|_err, e| e.set("code", "TOO_MANY_SUBSCRIPTIONS"))),
+        }
+    }
+
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Acquire)
+    }
+}
+
+/// A reserved subscription slot
+pub struct SubscriptionPermit {
+    active: Arc<AtomicUsize>,
+}
+
+impl Drop for SubscriptionPermit {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Validate an `Authorization` value the same way the REST middleware does
+async fn validate_authorization(auth_service: &Mutex<AuthService>, value: &str) -> Result<Claims, ApiError> {
+    let token = extract_token_from_header(value).map_err(|_| ApiError::Unauthorized {
+        message: "Invalid authorization header format".to_string(),
+    })?;
+    auth_service.lock().await.validate_token(token).map_err(|_| ApiError::Unauthorized {
+        message: "Invalid or expired token".to_string(),
+    })
+}
+
+/// Upgrade `GET /graphql` to a GraphQL WebSocket connection
+///
+/// Both `graphql-transport-ws` and the legacy `graphql-ws` protocols are accepted.
+/// Clients authenticate with an `Authorization` header on the handshake or, for
+/// browsers that cannot set headers, an `Authorization` field in the
+/// `connection_init` payload.
+pub async fn graphql_ws_upgrade(req: Request<Incoming>, schema: AppSchema, auth_service: Arc<Mutex<AuthService>>, ws_manager: Arc<WebSocketManager>) -> Result<Response<Full<Bytes>>, ApiError> {
+    let key = req
+        .headers()
+        .get("sec-websocket-key")
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| ApiError::BadRequest {
+            message: "Missing Sec-WebSocket-Key".to_string(),
+        })?
+        .to_string();
+
+    let protocol = req
+        .headers()
+        .get("sec-websocket-protocol")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|protocols| protocols.split(',').find_map(|p| WebSocketProtocols::from_str(p.trim()).ok()))
+        .unwrap_or(WebSocketProtocols::GraphQLWS);
+
+    // A token on the handshake is checked up front so bad credentials fail with 401
+    let header_claims = match req.headers().get("authorization") {
+        Some(value) => {
+            let value = value.to_str().map_err(|_| ApiError::Unauthorized {
+                message: "Invalid authorization header encoding".to_string(),
+            })?;
+            Some(validate_authorization(&auth_service, value).await?)
+        }
+        None => None,
+    };
+
+    const WEBSOCKET_HANDSHAKE_MAGIC: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+    let mut hasher = Sha1::new();
+    hasher.update(format!("{}{}", key, WEBSOCKET_HANDSHAKE_MAGIC).as_bytes());
+    let accept_key = general_purpose::STANDARD.encode(hasher.finalize());
+
+    let mut req = req;
+    tokio::spawn(async move {
+        let upgraded = match hyper::upgrade::on(&mut req).await {
+            Ok(upgraded) => upgraded,
+            Err(e) => {
+                error!("GraphQL WebSocket upgrade failed: {}", e);
+                return;
+            }
+        };
+
+        let ws_stream = WebSocketStream::from_raw_socket(TokioIo::new(upgraded), Role::Server, None).await;
+        ws_manager.connection_opened();
+        serve_connection(ws_stream, schema, protocol, header_claims, auth_service, ws_manager.clone()).await;
+        ws_manager.connection_closed();
+    });
+
+    Ok(Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header("Upgrade", "websocket")
+        .header("Connection", "Upgrade")
+        .header("Sec-WebSocket-Accept", accept_key)
+        .header("Sec-WebSocket-Protocol", protocol.sec_websocket_protocol())
+        .body(Full::new(Bytes::new()))?)
+}
+
+/// Run the GraphQL WebSocket protocol until either side closes
+///
+/// When the client goes away the incoming stream ends, which drops every active
+/// subscription stream and with it the upstream gRPC calls.
+async fn serve_connection(
+    ws_stream: WebSocketStream<TokioIo<hyper::upgrade::Upgraded>>,
+    schema: AppSchema,
+    protocol: WebSocketProtocols,
+    header_claims: Option<Claims>,
+    auth_service: Arc<Mutex<AuthService>>,
+    ws_manager: Arc<WebSocketManager>,
+) {
+    let (mut sink, stream) = ws_stream.split();
+
+    let metrics_manager = ws_manager.clone();
+    let incoming = stream
+        .take_while(|msg| futures::future::ready(matches!(msg, Ok(m) if !m.is_close())))
+        .filter_map(move |msg| {
+            let data = match msg {
+                Ok(Message::Text(text)) => Some(text.into_bytes()),
+                Ok(Message::Binary(bytes)) => Some(bytes),
+                _ => None,
+            };
+            if data.is_some() {
+                metrics_manager.message_received();
+            }
+            futures::future::ready(data)
+        });
+
+    let mut connection_data = Data::default();
+    connection_data.insert(SubscriptionLimiter::new(MAX_SUBSCRIPTIONS_PER_CONNECTION));
+    let has_header_claims = header_claims.is_some();
+    if let Some(claims) = header_claims {
+        connection_data.insert(claims);
+    }
+
+    let mut messages = GqlWebSocket::new(schema, incoming, protocol)
+        .connection_data(connection_data)
+        .on_connection_init(move |payload| async move {
+            let mut data = Data::default();
+            if has_header_claims {
+                return Ok(data);
+            }
+
+            let authorization = payload.get("Authorization").or_else(|| payload.get("authorization")).and_then(|v| v.as_str());
+            if let Some(value) = authorization {
+                let claims = validate_authorization(&auth_service, value).await.map_err(|e| async_graphql::Error::new(e.to_string()))?;
+                debug!("GraphQL WebSocket authenticated user {}", claims.sub);
+                data.insert(claims);
+            }
+            Ok(data)
+        });
+
+    info!("GraphQL WebSocket connection established ({})", protocol.sec_websocket_protocol());
+
+    let shutdown = ws_manager.shutdown().clone();
+    loop {
+        let message = tokio::select! {
+            message = messages.next() => match message {
+                Some(message) => message,
+                None => break,
+            },
+            _ = shutdown.triggered() => {
+                if let Err(e) = sink.send(shutdown_close_frame()).await {
+                    debug!("Failed to send close frame to GraphQL WebSocket: {}", e);
+                }
+                break;
+            }
+        };
+        let frame = match message {
+            WsMessage::Text(text) => Message::Text(text),
+            WsMessage::Close(code, reason) => Message::Close(Some(CloseFrame {
+                code: CloseCode::from(code),
+                reason: reason.into(),
+            })),
+        };
+        let closing = frame.is_close();
+
+        if let Err(e) = sink.send(frame).await {
+            warn!("Failed to write to GraphQL WebSocket: {}", e);
+            break;
+        }
+        ws_manager.message_sent();
+
+        if closing {
+            break;
+        }
+    }
+
+    info!("GraphQL WebSocket connection closed");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limiter_caps_active_subscriptions() {
+        let limiter = SubscriptionLimiter::new(2);
+
+        let first = limiter.acquire().unwrap();
+        let _second = limiter.acquire().unwrap();
+        assert_eq!(limiter.active(), 2);
+        assert!(limiter.acquire().is_err());
+
+        drop(first);
+        assert_eq!(limiter.active(), 1);
+        assert!(limiter.acquire().is_ok());
+    }
+
+    #[test]
+    fn test_limiter_is_shared_between_clones() {
+        let limiter = SubscriptionLimiter::new(1);
+        let clone = limiter.clone();
+
+        let _permit = limiter.acquire().unwrap();
+        assert!(clone.acquire().is_err());
+    }
+}
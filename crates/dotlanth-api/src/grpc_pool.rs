@@ -0,0 +1,744 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Pooled gRPC channels with health checking and retries
+//!
+//! Calls are spread round-robin over a fixed number of channels, skipping
+//! channels marked unhealthy. A channel is marked unhealthy when a call on it
+//! fails with `Unavailable` or when the periodic health probe fails; the health
+//! checker then re-dials it. Idempotent calls are retried with exponential
+//! backoff, limited by a retry budget so that retries cannot multiply load
+//! during an outage. Non-idempotent calls are never retried.
+//!
+//! With [`GrpcTlsConfig`] the channels use TLS, presenting a client certificate
+//! when the runtime requires mutual TLS. Certificate files are read again for
+//! every re-dial, so a rotated client certificate is picked up without a restart.
+//!
+//! Clients built on the channels call through [`RuntimeInterceptor`], which presents
+//! the gateway's API key to runtimes that require one.
+
+use crate::error::{ApiError, ApiResult};
+use crate::telemetry::propagate_trace;
+use parking_lot::{Mutex, RwLock};
+use serde::Serialize;
+use std::env;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::service::Interceptor;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Identity};
+use tonic::{Code, Request, Status};
+use tracing::{info, warn};
+
+/// Retry behavior for idempotent calls
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Attempts per call, including the first
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each further retry
+    pub initial_backoff: Duration,
+    /// Upper bound on the delay between attempts
+    pub max_backoff: Duration,
+    /// Retry tokens earned per call; 0.1 allows retries to add at most 10% to the call volume
+    pub budget_ratio: f64,
+    /// Retry tokens available up front, and the most the budget can hold
+    pub budget_reserve: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(1),
+            budget_ratio: 0.1,
+            budget_reserve: 10.0,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `retry`, counting from zero
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff.saturating_mul(2u32.saturating_pow(retry)).min(self.max_backoff)
+    }
+}
+
+/// Channel pool settings
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    /// Number of channels, each its own HTTP/2 connection
+    pub size: usize,
+    /// Timeout for establishing a connection
+    pub connect_timeout: Duration,
+    /// How often channels are probed and broken ones re-dialed
+    pub health_check_interval: Duration,
+    pub retry: RetryPolicy,
+    pub tls: GrpcTlsConfig,
+    /// API key presented to the runtime on every call, as `<key id>.<secret>`
+    pub api_key: Option<String>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            size: 4,
+            connect_timeout: Duration::from_secs(5),
+            health_check_interval: Duration::from_secs(10),
+            retry: RetryPolicy::default(),
+            tls: GrpcTlsConfig::default(),
+            api_key: None,
+        }
+    }
+}
+
+impl PoolConfig {
+    /// Load pool settings from `DOTLANTH_VM_POOL_*`, `DOTLANTH_VM_RETRY_*` and `DOTLANTH_VM_API_KEY`
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Some(size) = parse_env::<usize>("DOTLANTH_VM_POOL_SIZE").filter(|&size| size > 0) {
+            config.size = size;
+        }
+        if let Some(secs) = parse_env("DOTLANTH_VM_POOL_CONNECT_TIMEOUT_SECS") {
+            config.connect_timeout = Duration::from_secs(secs);
+        }
+        if let Some(secs) = parse_env::<u64>("DOTLANTH_VM_POOL_HEALTH_CHECK_INTERVAL_SECS").filter(|&secs| secs > 0) {
+            config.health_check_interval = Duration::from_secs(secs);
+        }
+        if let Some(attempts) = parse_env::<u32>("DOTLANTH_VM_RETRY_MAX_ATTEMPTS").filter(|&attempts| attempts > 0) {
+            config.retry.max_attempts = attempts;
+        }
+        if let Some(ms) = parse_env("DOTLANTH_VM_RETRY_INITIAL_BACKOFF_MS") {
+            config.retry.initial_backoff = Duration::from_millis(ms);
+        }
+        if let Some(ms) = parse_env("DOTLANTH_VM_RETRY_MAX_BACKOFF_MS") {
+            config.retry.max_backoff = Duration::from_millis(ms);
+        }
+        if let Some(ratio) = parse_env::<f64>("DOTLANTH_VM_RETRY_BUDGET_RATIO").filter(|ratio| (0.0..=1.0).contains(ratio)) {
+            config.retry.budget_ratio = ratio;
+        }
+        if let Some(reserve) = parse_env::<f64>("DOTLANTH_VM_RETRY_BUDGET_RESERVE").filter(|&reserve| reserve >= 0.0) {
+            config.retry.budget_reserve = reserve;
+        }
+        config.tls = GrpcTlsConfig::from_env();
+        config.api_key = env::var("DOTLANTH_VM_API_KEY").ok().filter(|key| !key.is_empty());
+
+        config
+    }
+}
+
+/// Interceptor of every call to the runtime: presents the gateway's API key as a bearer
+/// token and forwards the current request's trace context
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeInterceptor {
+    authorization: Option<MetadataValue<Ascii>>,
+}
+
+impl RuntimeInterceptor {
+    /// Interceptor presenting `api_key`, or calling without a key when there is none
+    pub fn new(api_key: Option<&str>) -> ApiResult<Self> {
+        let authorization = api_key
+            .map(|key| {
+                let mut value = MetadataValue::try_from(format!("Bearer {}", key)).map_err(|_| ApiError::InternalServerError {
+                    message: "DOTLANTH_VM_API_KEY is not a valid header value".to_string(),
+                })?;
+                value.set_sensitive(true);
+                Ok(value)
+            })
+            .transpose()?;
+        Ok(Self { authorization })
+    }
+}
+
+impl Interceptor for RuntimeInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(authorization) = &self.authorization {
+            request.metadata_mut().insert("authorization", authorization.clone());
+        }
+        propagate_trace(request)
+    }
+}
+
+/// Client-side TLS for channels to the runtime
+///
+/// TLS is used when the address is `https://` or any certificate is configured, and
+/// requires `ca_cert_path`: tonic is built without the system root store.
+#[derive(Debug, Clone, Default)]
+pub struct GrpcTlsConfig {
+    /// CA that signed the runtime's certificate
+    pub ca_cert_path: Option<PathBuf>,
+    /// Certificate presented to runtimes that require mutual TLS
+    pub client_cert_path: Option<PathBuf>,
+    pub client_key_path: Option<PathBuf>,
+    /// Name the runtime's certificate is checked against, if not the address's host
+    pub domain_name: Option<String>,
+    /// Connect in plaintext even to `https://` addresses; for local development only
+    pub insecure: bool,
+}
+
+impl GrpcTlsConfig {
+    /// Load TLS settings from `DOTLANTH_VM_TLS_*` variables
+    pub fn from_env() -> Self {
+        let path = |name: &str| env::var(name).ok().filter(|value| !value.is_empty()).map(PathBuf::from);
+        Self {
+            ca_cert_path: path("DOTLANTH_VM_TLS_CA_CERT"),
+            client_cert_path: path("DOTLANTH_VM_TLS_CLIENT_CERT"),
+            client_key_path: path("DOTLANTH_VM_TLS_CLIENT_KEY"),
+            domain_name: env::var("DOTLANTH_VM_TLS_DOMAIN").ok().filter(|name| !name.is_empty()),
+            insecure: parse_env("DOTLANTH_VM_TLS_INSECURE").unwrap_or(false),
+        }
+    }
+
+    fn enabled_for(&self, address: &str) -> bool {
+        !self.insecure && (address.starts_with("https://") || self.ca_cert_path.is_some() || self.client_cert_path.is_some())
+    }
+
+    /// Endpoint for `address`, with its scheme switched to match whether TLS is used
+    pub fn endpoint(&self, address: &str) -> ApiResult<Endpoint> {
+        let invalid = |message: String| ApiError::InternalServerError { message };
+        let tls = self.enabled_for(address);
+        let address = match (tls, address.split_once("://")) {
+            (true, Some(("http", rest))) => format!("https://{}", rest),
+            (false, Some(("https", rest))) => {
+                warn!("TLS to {} is turned off by DOTLANTH_VM_TLS_INSECURE", address);
+                format!("http://{}", rest)
+            }
+            _ => address.to_string(),
+        };
+
+        let endpoint = Channel::from_shared(address.clone()).map_err(|e| invalid(format!("Invalid endpoint {}: {}", address, e)))?;
+        if !tls {
+            return Ok(endpoint);
+        }
+
+        let ca_cert_path = self
+            .ca_cert_path
+            .as_deref()
+            .ok_or_else(|| invalid(format!("TLS to {} needs a CA certificate (DOTLANTH_VM_TLS_CA_CERT)", address)))?;
+        let mut tls_config = ClientTlsConfig::new().ca_certificate(Certificate::from_pem(read_pem(ca_cert_path)?));
+        match (&self.client_cert_path, &self.client_key_path) {
+            (Some(cert), Some(key)) => tls_config = tls_config.identity(Identity::from_pem(read_pem(cert)?, read_pem(key)?)),
+            (None, None) => {}
+            _ => return Err(invalid("A client certificate and key must be configured together".to_string())),
+        }
+        if let Some(domain_name) = &self.domain_name {
+            tls_config = tls_config.domain_name(domain_name.clone());
+        }
+
+        endpoint.tls_config(tls_config).map_err(|e| invalid(format!("Invalid TLS configuration for {}: {}", address, e)))
+    }
+}
+
+fn read_pem(path: &Path) -> ApiResult<Vec<u8>> {
+    std::fs::read(path).map_err(|e| ApiError::InternalServerError {
+        message: format!("Failed to read {}: {}", path.display(), e),
+    })
+}
+
+pub(crate) fn parse_env<T: std::str::FromStr>(name: &str) -> Option<T> {
+    let value = env::var(name).ok()?;
+    let parsed = value.parse().ok();
+    if parsed.is_none() {
+        warn!("Ignoring {}: invalid value '{}'", name, value);
+    }
+    parsed
+}
+
+/// Whether a call may safely run more than once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Idempotency {
+    Idempotent,
+    NonIdempotent,
+}
+
+/// Errors that indicate the connection rather than the request failed
+fn is_connection_failure(status: &Status) -> bool {
+    status.code() == Code::Unavailable
+}
+
+fn is_retryable(status: &Status) -> bool {
+    matches!(status.code(), Code::Unavailable | Code::DeadlineExceeded)
+}
+
+/// Token bucket that caps retries to a fraction of calls
+#[derive(Debug)]
+struct RetryBudget {
+    tokens: Mutex<f64>,
+    ratio: f64,
+    reserve: f64,
+}
+
+impl RetryBudget {
+    fn new(policy: &RetryPolicy) -> Self {
+        Self {
+            tokens: Mutex::new(policy.budget_reserve),
+            ratio: policy.budget_ratio,
+            reserve: policy.budget_reserve,
+        }
+    }
+
+    fn deposit(&self) {
+        let mut tokens = self.tokens.lock();
+        *tokens = (*tokens + self.ratio).min(self.reserve);
+    }
+
+    fn try_withdraw(&self) -> bool {
+        let mut tokens = self.tokens.lock();
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn remaining(&self) -> f64 {
+        *self.tokens.lock()
+    }
+}
+
+/// One pooled channel
+#[derive(Debug)]
+struct Slot {
+    channel: RwLock<Channel>,
+    healthy: AtomicBool,
+    in_flight: AtomicUsize,
+}
+
+impl Slot {
+    fn new(channel: Channel) -> Self {
+        Self {
+            channel: RwLock::new(channel),
+            healthy: AtomicBool::new(true),
+            in_flight: AtomicUsize::new(0),
+        }
+    }
+
+    fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+}
+
+/// A checked-out channel, counted as active until dropped
+struct Checkout {
+    slot: Arc<Slot>,
+    channel: Channel,
+}
+
+impl Drop for Checkout {
+    fn drop(&mut self) {
+        self.slot.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Default)]
+struct PoolCounters {
+    retries: AtomicU64,
+    retries_exhausted: AtomicU64,
+    retry_budget_exhausted: AtomicU64,
+    health_check_failures: AtomicU64,
+    redials: AtomicU64,
+    redial_failures: AtomicU64,
+}
+
+/// Snapshot of pool state and counters
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolStats {
+    pub size: usize,
+    /// Channels with calls in flight
+    pub active: usize,
+    /// Healthy channels with no calls in flight
+    pub idle: usize,
+    /// Channels waiting to be re-dialed
+    pub unhealthy: usize,
+    /// Retries performed
+    pub retries: u64,
+    /// Calls that failed after using every attempt
+    pub retries_exhausted: u64,
+    /// Retries skipped because the retry budget was empty
+    pub retry_budget_exhausted: u64,
+    pub retry_budget_remaining: f64,
+    pub health_check_failures: u64,
+    pub redials: u64,
+    pub redial_failures: u64,
+}
+
+/// Fixed-size pool of gRPC channels to one endpoint
+#[derive(Debug)]
+pub struct ChannelPool {
+    address: String,
+    endpoint: Endpoint,
+    config: PoolConfig,
+    interceptor: RuntimeInterceptor,
+    slots: Vec<Arc<Slot>>,
+    next: AtomicUsize,
+    budget: RetryBudget,
+    counters: PoolCounters,
+}
+
+impl ChannelPool {
+    /// Dial every channel up front, failing if the endpoint is unreachable
+    pub async fn connect(address: &str, config: PoolConfig) -> ApiResult<Self> {
+        let endpoint = Self::endpoint(address, &config)?;
+        let interceptor = RuntimeInterceptor::new(config.api_key.as_deref())?;
+        let mut channels = Vec::with_capacity(config.size.max(1));
+        for _ in 0..config.size.max(1) {
+            let channel = endpoint.connect().await.map_err(|e| ApiError::InternalServerError {
+                message: format!("Failed to connect to gRPC endpoint {}: {}", address, e),
+            })?;
+            channels.push(channel);
+        }
+
+        info!("Connected {} channel(s) to {}", channels.len(), address);
+        Ok(Self::from_channels(address, endpoint, config, interceptor, channels))
+    }
+
+    /// Create the pool without dialing; connections are made on first use
+    pub fn connect_lazy(address: &str, config: PoolConfig) -> ApiResult<Self> {
+        let endpoint = Self::endpoint(address, &config)?;
+        let interceptor = RuntimeInterceptor::new(config.api_key.as_deref())?;
+        let channels = (0..config.size.max(1)).map(|_| endpoint.connect_lazy()).collect();
+        Ok(Self::from_channels(address, endpoint, config, interceptor, channels))
+    }
+
+    fn endpoint(address: &str, config: &PoolConfig) -> ApiResult<Endpoint> {
+        Ok(config.tls.endpoint(address)?.connect_timeout(config.connect_timeout))
+    }
+
+    /// TLS settings the channels were dialed with
+    pub fn tls(&self) -> &GrpcTlsConfig {
+        &self.config.tls
+    }
+
+    /// Interceptor clients built on the pooled channels call through
+    pub fn interceptor(&self) -> RuntimeInterceptor {
+        self.interceptor.clone()
+    }
+
+    fn from_channels(address: &str, endpoint: Endpoint, config: PoolConfig, interceptor: RuntimeInterceptor, channels: Vec<Channel>) -> Self {
+        Self {
+            address: address.to_string(),
+            endpoint,
+            budget: RetryBudget::new(&config.retry),
+            config,
+            interceptor,
+            slots: channels.into_iter().map(|channel| Arc::new(Slot::new(channel))).collect(),
+            next: AtomicUsize::new(0),
+            counters: PoolCounters::default(),
+        }
+    }
+
+    /// Next healthy channel in round-robin order, or any channel when none is healthy
+    fn checkout(&self) -> Checkout {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let count = self.slots.len();
+        let slot = (0..count)
+            .map(|offset| &self.slots[(start + offset) % count])
+            .find(|slot| slot.is_healthy())
+            .unwrap_or(&self.slots[start % count])
+            .clone();
+
+        slot.in_flight.fetch_add(1, Ordering::Relaxed);
+        let channel = slot.channel.read().clone();
+        Checkout { slot, channel }
+    }
+
+    /// A channel for long-lived calls such as streams, which are not retried or counted
+    pub fn channel(&self) -> Channel {
+        let checkout = self.checkout();
+        checkout.channel.clone()
+    }
+
+    /// Run `call` on a pooled channel, retrying idempotent calls that fail with a transient error
+    pub async fn call<T, F, Fut>(&self, idempotency: Idempotency, mut call: F) -> Result<T, Status>
+    where
+        F: FnMut(Channel) -> Fut,
+        Fut: Future<Output = Result<T, Status>>,
+    {
+        self.budget.deposit();
+        let mut attempt = 1;
+
+        loop {
+            let checkout = self.checkout();
+            let status = match call(checkout.channel.clone()).await {
+                Ok(response) => return Ok(response),
+                Err(status) => status,
+            };
+            if is_connection_failure(&status) {
+                checkout.slot.healthy.store(false, Ordering::Relaxed);
+            }
+            drop(checkout);
+
+            if idempotency == Idempotency::NonIdempotent || !is_retryable(&status) {
+                return Err(status);
+            }
+            if attempt >= self.config.retry.max_attempts {
+                self.counters.retries_exhausted.fetch_add(1, Ordering::Relaxed);
+                return Err(status);
+            }
+            if !self.budget.try_withdraw() {
+                self.counters.retry_budget_exhausted.fetch_add(1, Ordering::Relaxed);
+                return Err(status);
+            }
+
+            let backoff = self.config.retry.backoff(attempt - 1);
+            warn!(
+                "gRPC call failed ({}), retrying in {:?} (attempt {} of {})",
+                status.code(),
+                backoff,
+                attempt + 1,
+                self.config.retry.max_attempts
+            );
+            self.counters.retries.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(backoff).await;
+            attempt += 1;
+        }
+    }
+
+    /// Probe healthy channels and re-dial unhealthy ones every `health_check_interval`
+    pub fn spawn_health_checker<P, Fut>(self: &Arc<Self>, probe: P) -> JoinHandle<()>
+    where
+        P: Fn(Channel) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = bool> + Send,
+    {
+        let pool = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(pool.config.health_check_interval);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                pool.check_health(&probe).await;
+            }
+        })
+    }
+
+    async fn check_health<P, Fut>(&self, probe: &P)
+    where
+        P: Fn(Channel) -> Fut,
+        Fut: Future<Output = bool>,
+    {
+        for (index, slot) in self.slots.iter().enumerate() {
+            if slot.is_healthy() {
+                let channel = slot.channel.read().clone();
+                if !probe(channel).await {
+                    warn!("Channel {} failed its health check", index);
+                    self.counters.health_check_failures.fetch_add(1, Ordering::Relaxed);
+                    slot.healthy.store(false, Ordering::Relaxed);
+                }
+                continue;
+            }
+
+            // Certificates may have been rotated since the pool was created
+            let endpoint = Self::endpoint(&self.address, &self.config).unwrap_or_else(|e| {
+                warn!("Re-dialing with the previous TLS settings: {}", e);
+                self.endpoint.clone()
+            });
+            match endpoint.connect().await {
+                Ok(channel) if probe(channel.clone()).await => {
+                    *slot.channel.write() = channel;
+                    slot.healthy.store(true, Ordering::Relaxed);
+                    self.counters.redials.fetch_add(1, Ordering::Relaxed);
+                    info!("Re-dialed channel {}", index);
+                }
+                Ok(_) => {
+                    self.counters.redial_failures.fetch_add(1, Ordering::Relaxed);
+                    warn!("Re-dialed channel {} is not serving yet", index);
+                }
+                Err(e) => {
+                    self.counters.redial_failures.fetch_add(1, Ordering::Relaxed);
+                    warn!("Failed to re-dial channel {}: {}", index, e);
+                }
+            }
+        }
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        let mut stats = PoolStats {
+            size: self.slots.len(),
+            active: 0,
+            idle: 0,
+            unhealthy: 0,
+            retries: self.counters.retries.load(Ordering::Relaxed),
+            retries_exhausted: self.counters.retries_exhausted.load(Ordering::Relaxed),
+            retry_budget_exhausted: self.counters.retry_budget_exhausted.load(Ordering::Relaxed),
+            retry_budget_remaining: self.budget.remaining(),
+            health_check_failures: self.counters.health_check_failures.load(Ordering::Relaxed),
+            redials: self.counters.redials.load(Ordering::Relaxed),
+            redial_failures: self.counters.redial_failures.load(Ordering::Relaxed),
+        };
+        for slot in &self.slots {
+            if slot.in_flight.load(Ordering::Relaxed) > 0 {
+                stats.active += 1;
+            } else if slot.is_healthy() {
+                stats.idle += 1;
+            }
+            if !slot.is_healthy() {
+                stats.unhealthy += 1;
+            }
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU32;
+
+    fn pool(retry: RetryPolicy) -> ChannelPool {
+        let config = PoolConfig {
+            size: 2,
+            retry: RetryPolicy {
+                initial_backoff: Duration::from_millis(1),
+                ..retry
+            },
+            ..Default::default()
+        };
+        ChannelPool::connect_lazy("http://127.0.0.1:1", config).unwrap()
+    }
+
+    /// A call that fails with `code` the first `failures` times
+    fn flaky(failures: u32, code: Code) -> (Arc<AtomicU32>, impl FnMut(Channel) -> std::future::Ready<Result<u32, Status>>) {
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        let call = move |_channel| {
+            let n = counter.fetch_add(1, Ordering::Relaxed);
+            std::future::ready(if n < failures { Err(Status::new(code, "down")) } else { Ok(n) })
+        };
+        (calls, call)
+    }
+
+    #[test]
+    fn test_tls_follows_address_and_certificates() {
+        let plain = GrpcTlsConfig::default();
+        assert_eq!(plain.endpoint("http://127.0.0.1:50051").unwrap().uri().scheme_str(), Some("http"));
+
+        // https needs a CA, and configuring one upgrades http addresses
+        let err = plain.endpoint("https://runtime:50051").unwrap_err();
+        assert!(err.to_string().contains("DOTLANTH_VM_TLS_CA_CERT"));
+        let with_ca = GrpcTlsConfig {
+            ca_cert_path: Some(PathBuf::from("/nonexistent/ca.pem")),
+            ..Default::default()
+        };
+        assert!(with_ca.endpoint("http://runtime:50051").unwrap_err().to_string().contains("/nonexistent/ca.pem"));
+
+        let insecure = GrpcTlsConfig { insecure: true, ..with_ca };
+        assert_eq!(insecure.endpoint("https://runtime:50051").unwrap().uri().scheme_str(), Some("http"));
+    }
+
+    #[test]
+    fn test_interceptor_presents_the_api_key() {
+        let mut interceptor = RuntimeInterceptor::new(Some("gateway.s3cr3t")).unwrap();
+        let request = interceptor.call(Request::new(())).unwrap();
+        assert_eq!(request.metadata().get("authorization").unwrap(), "Bearer gateway.s3cr3t");
+        assert!(!format!("{:?}", interceptor).contains("s3cr3t"));
+
+        let request = RuntimeInterceptor::new(None).unwrap().call(Request::new(())).unwrap();
+        assert!(request.metadata().get("authorization").is_none());
+        assert!(RuntimeInterceptor::new(Some("bad\nkey")).is_err());
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_cap() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(300),
+            ..Default::default()
+        };
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(1), Duration::from_millis(200));
+        assert_eq!(policy.backoff(2), Duration::from_millis(300));
+        assert_eq!(policy.backoff(40), Duration::from_millis(300));
+    }
+
+    #[tokio::test]
+    async fn test_idempotent_calls_retry_transient_failures() {
+        let pool = pool(RetryPolicy::default());
+        let (calls, call) = flaky(2, Code::Unavailable);
+
+        assert_eq!(pool.call(Idempotency::Idempotent, call).await.unwrap(), 2);
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+
+        let stats = pool.stats();
+        assert_eq!(stats.retries, 2);
+        // Both channels failed with Unavailable and wait for the health checker
+        assert_eq!(stats.unhealthy, 2);
+        assert_eq!(stats.active, 0);
+    }
+
+    #[tokio::test]
+    async fn test_non_idempotent_and_permanent_failures_are_not_retried() {
+        let pool = pool(RetryPolicy::default());
+
+        let (calls, call) = flaky(1, Code::Unavailable);
+        assert!(pool.call(Idempotency::NonIdempotent, call).await.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        let (calls, call) = flaky(1, Code::NotFound);
+        assert_eq!(pool.call(Idempotency::Idempotent, call).await.unwrap_err().code(), Code::NotFound);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        assert_eq!(pool.stats().retries, 0);
+    }
+
+    #[tokio::test]
+    async fn test_attempts_and_budget_limit_retries() {
+        let pool = pool(RetryPolicy {
+            max_attempts: 3,
+            budget_reserve: 3.0,
+            budget_ratio: 0.0,
+            ..Default::default()
+        });
+
+        let (calls, call) = flaky(u32::MAX, Code::Unavailable);
+        assert!(pool.call(Idempotency::Idempotent, call).await.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+        assert_eq!(pool.stats().retries_exhausted, 1);
+
+        // One token left: the next outage gets a single retry before the budget runs dry
+        let (calls, call) = flaky(u32::MAX, Code::Unavailable);
+        assert!(pool.call(Idempotency::Idempotent, call).await.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+
+        let stats = pool.stats();
+        assert_eq!(stats.retries, 3);
+        assert_eq!(stats.retry_budget_exhausted, 1);
+    }
+
+    #[test]
+    fn test_budget_refills_with_successful_traffic() {
+        let budget = RetryBudget::new(&RetryPolicy {
+            budget_ratio: 0.5,
+            budget_reserve: 1.0,
+            ..Default::default()
+        });
+        assert!(budget.try_withdraw());
+        assert!(!budget.try_withdraw());
+
+        budget.deposit();
+        assert!(!budget.try_withdraw());
+        budget.deposit();
+        assert!(budget.try_withdraw());
+
+        // Deposits never exceed the reserve
+        (0..10).for_each(|_| budget.deposit());
+        assert_eq!(budget.remaining(), 1.0);
+    }
+}
@@ -0,0 +1,384 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! ABI-driven validation of dot execution requests
+//!
+//! Execution inputs are checked against the input types declared in the dot's
+//! ABI before the request reaches the runtime. ABIs are cached per dot and
+//! invalidated when the dot is redeployed or deleted.
+
+use crate::error::ApiResult;
+use crate::models::{AbiFieldType, DotInputSchema, ExecuteDotRequest, FieldError};
+use crate::vm::VmClient;
+use base64::Engine;
+use dashmap::DashMap;
+use futures::StreamExt;
+use hyper::header::HeaderMap;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Request header that skips ABI validation, for debugging dots with incomplete ABIs
+pub const SKIP_VALIDATION_HEADER: &str = "x-dotlanth-skip-validation";
+
+/// Delay before resubscribing after the dot event stream drops
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(5);
+
+/// ABI validation settings
+#[derive(Debug, Clone)]
+pub struct AbiValidationConfig {
+    /// Reject inputs the ABI does not declare
+    pub strict: bool,
+
+    /// Upper bound on how long a cached ABI is trusted, in case an invalidation event is missed
+    pub cache_ttl: Duration,
+}
+
+impl Default for AbiValidationConfig {
+    fn default() -> Self {
+        Self {
+            strict: false,
+            cache_ttl: Duration::from_secs(300),
+        }
+    }
+}
+
+struct CachedAbi {
+    /// `None` when the dot has no registered ABI, so such dots are not re-fetched on every call
+    schema: Option<Arc<DotInputSchema>>,
+    fetched_at: Instant,
+}
+
+/// Per-dot cache of ABI input schemas
+pub struct AbiCache {
+    config: AbiValidationConfig,
+    entries: DashMap<String, CachedAbi>,
+}
+
+impl AbiCache {
+    pub fn new(config: AbiValidationConfig) -> Self {
+        Self { config, entries: DashMap::new() }
+    }
+
+    pub fn config(&self) -> &AbiValidationConfig {
+        &self.config
+    }
+
+    /// Get a dot's input schema, fetching it from the runtime on a miss
+    pub async fn get(&self, dot_id: &str, vm_client: &VmClient) -> ApiResult<Option<Arc<DotInputSchema>>> {
+        if let Some(cached) = self.entries.get(dot_id)
+            && cached.fetched_at.elapsed() < self.config.cache_ttl
+        {
+            return Ok(cached.schema.clone());
+        }
+
+        let schema = vm_client.get_dot_abi(dot_id).await?.map(Arc::new);
+        self.insert(dot_id, schema.clone());
+        Ok(schema)
+    }
+
+    pub fn insert(&self, dot_id: &str, schema: Option<Arc<DotInputSchema>>) {
+        self.entries.insert(
+            dot_id.to_string(),
+            CachedAbi {
+                schema,
+                fetched_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Drop a dot's cached ABI; returns whether one was cached
+    pub fn invalidate(&self, dot_id: &str) -> bool {
+        self.entries.remove(dot_id).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Subscribe to dot events and invalidate cached ABIs of redeployed or deleted dots.
+    /// The task resubscribes if the stream drops and exits once the cache is dropped.
+    pub fn spawn_invalidation_listener(self: &Arc<Self>, vm_client: VmClient) -> JoinHandle<()> {
+        let cache: Weak<Self> = Arc::downgrade(self);
+        tokio::spawn(async move {
+            loop {
+                match vm_client.stream_all_dot_events(vec![]).await {
+                    Ok(mut events) => {
+                        info!("Listening for dot events to invalidate cached ABIs");
+                        while let Some(event) = events.next().await {
+                            let Some(cache) = cache.upgrade() else {
+                                return;
+                            };
+                            match event {
+                                Ok(event) if invalidates_abi(&event.event_type) => {
+                                    if cache.invalidate(&event.dot_id) {
+                                        debug!("Invalidated cached ABI for dot {} after {} event", event.dot_id, event.event_type);
+                                    }
+                                }
+                                Ok(_) => {}
+                                Err(e) => {
+                                    warn!("Dot event stream failed: {}", e);
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => warn!("Failed to subscribe to dot events for ABI invalidation: {}", e),
+                }
+
+                if cache.strong_count() == 0 {
+                    return;
+                }
+                tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+            }
+        })
+    }
+}
+
+/// Whether a dot event means its ABI may have changed
+fn invalidates_abi(event_type: &str) -> bool {
+    let event_type = event_type.to_ascii_lowercase();
+    ["deploy", "delete", "abi"].iter().any(|kind| event_type.contains(kind))
+}
+
+/// Whether the request asked to bypass validation via [`SKIP_VALIDATION_HEADER`]
+pub fn skip_requested(headers: &HeaderMap) -> bool {
+    headers
+        .get(SKIP_VALIDATION_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+}
+
+/// Validate an execution request against a dot's declared inputs.
+///
+/// Positional `arguments` bind to the ABI inputs in declaration order, named
+/// `inputs` bind by name. Returns one error per offending field; an empty list
+/// means the request is valid.
+pub fn validate_execute_request(schema: &DotInputSchema, request: &ExecuteDotRequest, strict: bool) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    let mut provided: HashMap<&str, (String, &Value)> = HashMap::new();
+
+    for (i, value) in request.arguments.iter().enumerate() {
+        match schema.inputs.get(i) {
+            Some(field) => {
+                provided.insert(field.name.as_str(), (format!("arguments[{i}]"), value));
+            }
+            None if strict => errors.push(FieldError::new(format!("arguments[{i}]"), format!("unexpected argument; the ABI declares {} inputs", schema.inputs.len()))),
+            None => {}
+        }
+    }
+
+    let mut names: Vec<&String> = request.inputs.keys().collect();
+    names.sort();
+    for name in names {
+        let path = format!("inputs.{name}");
+        let Some(field) = schema.inputs.iter().find(|field| &field.name == name) else {
+            if strict {
+                errors.push(FieldError::new(path, "unknown field"));
+            }
+            continue;
+        };
+        if provided.insert(field.name.as_str(), (path.clone(), &request.inputs[name])).is_some() {
+            errors.push(FieldError::new(path, "also passed as a positional argument"));
+        }
+    }
+
+    for field in &schema.inputs {
+        match provided.get(field.name.as_str()) {
+            None | Some((_, Value::Null)) if field.required => {
+                let path = provided.get(field.name.as_str()).map(|(path, _)| path.clone()).unwrap_or_else(|| format!("inputs.{}", field.name));
+                errors.push(FieldError::new(path, "required field is missing"));
+            }
+            None | Some((_, Value::Null)) => {}
+            Some((path, value)) => {
+                check_type(&field.field_type, value, path, &mut errors);
+                if !field.enum_values.is_empty() {
+                    let matches = value.as_str().map(str::to_string).unwrap_or_else(|| value.to_string());
+                    if !field.enum_values.contains(&matches) {
+                        errors.push(FieldError::new(path.clone(), format!("must be one of: {}", field.enum_values.join(", "))));
+                    }
+                }
+            }
+        }
+    }
+
+    errors
+}
+
+/// Check `value` against an ABI type, recursing into array elements
+fn check_type(field_type: &AbiFieldType, value: &Value, path: &str, errors: &mut Vec<FieldError>) {
+    let valid = match field_type.name.to_ascii_lowercase().as_str() {
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "float" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "currency" => value.is_number() || value.as_str().is_some_and(|s| s.parse::<f64>().is_ok()),
+        "binary" => value.as_str().is_some_and(|s| base64::engine::general_purpose::STANDARD.decode(s).is_ok()),
+        "datetime" => value.as_str().is_some_and(|s| chrono::DateTime::parse_from_rfc3339(s).is_ok()),
+        "uuid" => value.as_str().is_some_and(|s| uuid::Uuid::parse_str(s).is_ok()),
+        "array" => match value.as_array() {
+            Some(items) => {
+                if let Some(element_type) = field_type.params.first() {
+                    for (i, item) in items.iter().enumerate() {
+                        check_type(element_type, item, &format!("{path}[{i}]"), errors);
+                    }
+                }
+                true
+            }
+            None => false,
+        },
+        // Types the gateway does not know are left to the runtime
+        _ => true,
+    };
+
+    if !valid {
+        errors.push(FieldError::new(path, format!("expected {}, got {}", describe_type(field_type), json_type_name(value))));
+    }
+}
+
+fn describe_type(field_type: &AbiFieldType) -> String {
+    match field_type.name.to_ascii_lowercase().as_str() {
+        "binary" => "base64-encoded Binary".to_string(),
+        "datetime" => "RFC 3339 DateTime".to_string(),
+        _ if field_type.params.is_empty() => field_type.name.clone(),
+        _ => format!("{}<{}>", field_type.name, field_type.params.iter().map(describe_type).collect::<Vec<_>>().join(", ")),
+    }
+}
+
+fn json_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "float",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AbiInputField;
+    use serde_json::json;
+
+    fn field(name: &str, type_name: &str, required: bool) -> AbiInputField {
+        AbiInputField {
+            name: name.to_string(),
+            field_type: AbiFieldType {
+                name: type_name.to_string(),
+                params: vec![],
+            },
+            required,
+            enum_values: vec![],
+        }
+    }
+
+    fn schema() -> DotInputSchema {
+        let mut tags = field("tags", "Array", false);
+        tags.field_type.params.push(AbiFieldType {
+            name: "String".to_string(),
+            params: vec![],
+        });
+        let mut mode = field("mode", "String", false);
+        mode.enum_values = vec!["fast".to_string(), "safe".to_string()];
+
+        DotInputSchema {
+            version: "1.0.0".to_string(),
+            inputs: vec![field("recipient", "String", true), field("amount", "Integer", true), tags, mode],
+        }
+    }
+
+    fn request(arguments: Vec<Value>, inputs: Value) -> ExecuteDotRequest {
+        ExecuteDotRequest {
+            function: "transfer".to_string(),
+            arguments,
+            inputs: serde_json::from_value(inputs).unwrap(),
+            context: None,
+        }
+    }
+
+    #[test]
+    fn test_valid_named_and_positional_inputs() {
+        let schema = schema();
+        assert!(validate_execute_request(&schema, &request(vec![], json!({"recipient": "bob", "amount": 5})), true).is_empty());
+        assert!(validate_execute_request(&schema, &request(vec![json!("bob"), json!(5), json!(["a"])], json!({"mode": "safe"})), true).is_empty());
+    }
+
+    #[test]
+    fn test_reports_missing_and_mistyped_fields() {
+        let errors = validate_execute_request(&schema(), &request(vec![], json!({"amount": "5", "tags": ["a", 1], "mode": "slow"})), false);
+
+        assert_eq!(
+            errors,
+            vec![
+                FieldError::new("inputs.recipient", "required field is missing"),
+                FieldError::new("inputs.amount", "expected Integer, got string"),
+                FieldError::new("inputs.tags[1]", "expected String, got integer"),
+                FieldError::new("inputs.mode", "must be one of: fast, safe"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_fields_only_rejected_in_strict_mode() {
+        let schema = schema();
+        let req = request(vec![json!("bob"), json!(5), json!([]), json!("fast"), json!(1)], json!({"memo": "hi"}));
+
+        assert!(validate_execute_request(&schema, &req, false).is_empty());
+        let errors = validate_execute_request(&schema, &req, true);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].field, "arguments[4]");
+        assert_eq!(errors[1], FieldError::new("inputs.memo", "unknown field"));
+    }
+
+    #[test]
+    fn test_rejects_input_passed_twice() {
+        let errors = validate_execute_request(&schema(), &request(vec![json!("bob"), json!(5)], json!({"amount": 6})), false);
+        assert_eq!(errors, vec![FieldError::new("inputs.amount", "also passed as a positional argument")]);
+    }
+
+    #[test]
+    fn test_skip_header_and_invalidating_events() {
+        let mut headers = HeaderMap::new();
+        assert!(!skip_requested(&headers));
+        headers.insert(SKIP_VALIDATION_HEADER, "true".parse().unwrap());
+        assert!(skip_requested(&headers));
+
+        assert!(invalidates_abi("DotDeployed"));
+        assert!(invalidates_abi("dot.deleted"));
+        assert!(!invalidates_abi("ExecutionCompleted"));
+    }
+
+    #[test]
+    fn test_cache_invalidate() {
+        let cache = AbiCache::new(AbiValidationConfig::default());
+        cache.insert("dot", Some(Arc::new(schema())));
+        assert_eq!(cache.len(), 1);
+        assert!(cache.invalidate("dot"));
+        assert!(!cache.invalidate("dot"));
+        assert!(cache.is_empty());
+    }
+}
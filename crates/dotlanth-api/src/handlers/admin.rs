@@ -0,0 +1,198 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Administration handlers
+
+use crate::audit::{AuditLogger, AuditQuery};
+use crate::auth::extract_token_from_header;
+use crate::compatibility_testing::runner::{ContractRunner, RunnerConfig};
+use crate::compatibility_testing::suite::builtin_cases;
+use crate::error::ApiError;
+use crate::middleware::extract_claims;
+use chrono::{DateTime, Utc};
+use http_body_util::Full;
+use hyper::{Request, Response, StatusCode, body::Bytes};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Records returned when the query does not set a limit
+const DEFAULT_AUDIT_LIMIT: usize = 100;
+const MAX_AUDIT_LIMIT: usize = 1000;
+
+/// Query the audit log of mutating requests
+/// GET /api/v1/admin/audit
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/audit",
+    params(
+        ("from" = Option<String>, Query, description = "Earliest timestamp (RFC 3339), inclusive"),
+        ("to" = Option<String>, Query, description = "Latest timestamp (RFC 3339), exclusive"),
+        ("principal" = Option<String>, Query, description = "Only requests made by this principal"),
+        ("limit" = Option<usize>, Query, description = "Most records to return, newest first (default 100, max 1000)")
+    ),
+    responses(
+        (status = 200, description = "Matching audit records, newest first"),
+        (status = 400, description = "Invalid query parameter"),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "Audit logging is not configured")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Admin"
+)]
+pub async fn query_audit_log(req: Request<hyper::body::Incoming>, query_params: HashMap<String, String>, audit_logger: Option<Arc<AuditLogger>>) -> Result<Response<Full<Bytes>>, ApiError> {
+    let claims = extract_claims(&req)?;
+    if !claims.has_role("admin") {
+        return Err(ApiError::Forbidden {
+            message: "The audit log is only available to admins".to_string(),
+        });
+    }
+
+    let audit_logger = audit_logger.ok_or_else(|| ApiError::NotFound {
+        message: "Audit logging is not configured".to_string(),
+    })?;
+
+    let limit = match query_params.get("limit") {
+        Some(limit) => limit.parse::<usize>().map_err(|_| ApiError::BadRequest {
+            message: format!("Invalid limit: {}", limit),
+        })?,
+        None => DEFAULT_AUDIT_LIMIT,
+    };
+    let query = AuditQuery {
+        from: parse_timestamp(&query_params, "from")?,
+        to: parse_timestamp(&query_params, "to")?,
+        principal: query_params.get("principal").cloned(),
+        limit: Some(limit.min(MAX_AUDIT_LIMIT)),
+    };
+    let records = audit_logger.query(&query).await?;
+
+    let response = serde_json::json!({
+        "records": records,
+        "dropped": audit_logger.dropped(),
+        "failed": audit_logger.failed(),
+        "retention_days": audit_logger.config().retention_days,
+    });
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("content-type", "application/json")
+        .body(Full::new(Bytes::from(serde_json::to_string(&response)?)))?)
+}
+
+/// Where the admin contract check sends its requests, and the lock that keeps
+/// runs from overlapping
+pub struct CompatCheck {
+    target: String,
+    running: Mutex<()>,
+}
+
+impl CompatCheck {
+    /// Check the gateway listening on `address`; an unspecified address is reached over loopback
+    pub fn new(address: SocketAddr) -> Self {
+        let mut address = address;
+        if address.ip().is_unspecified() {
+            address.set_ip(if address.is_ipv4() { [127, 0, 0, 1].into() } else { std::net::Ipv6Addr::LOCALHOST.into() });
+        }
+        Self {
+            target: format!("http://{}", address),
+            running: Mutex::new(()),
+        }
+    }
+}
+
+/// Run the built-in contract cases against this gateway
+/// POST /api/v1/admin/compat-check
+///
+/// Runs restricted: cases that write are skipped, and the rest send the caller's
+/// own token, so they see what the caller is allowed to see.
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/compat-check",
+    params(
+        ("format" = Option<String>, Query, description = "Report format, `json` (default) or `junit`")
+    ),
+    responses(
+        (status = 200, description = "Contract report; failed cases are reported, not turned into an error status"),
+        (status = 400, description = "Unknown report format"),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "The contract check is not configured"),
+        (status = 409, description = "A contract check is already running")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Admin"
+)]
+pub async fn run_compat_check(req: Request<hyper::body::Incoming>, query_params: HashMap<String, String>, compat_check: Option<Arc<CompatCheck>>) -> Result<Response<Full<Bytes>>, ApiError> {
+    let claims = extract_claims(&req)?;
+    if !claims.has_role("admin") {
+        return Err(ApiError::Forbidden {
+            message: "The contract check is only available to admins".to_string(),
+        });
+    }
+
+    let compat_check = compat_check.ok_or_else(|| ApiError::NotFound {
+        message: "The contract check is not configured".to_string(),
+    })?;
+
+    let junit = match query_params.get("format").map(String::as_str) {
+        None | Some("json") => false,
+        Some("junit") => true,
+        Some(other) => {
+            return Err(ApiError::BadRequest {
+                message: format!("Unknown report format '{}', expected json or junit", other),
+            });
+        }
+    };
+
+    let token = req
+        .headers()
+        .get("authorization")
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| extract_token_from_header(header).ok())
+        .map(str::to_string);
+
+    let _running = compat_check.running.try_lock().map_err(|_| ApiError::Conflict {
+        message: "A contract check is already running".to_string(),
+    })?;
+
+    let mut config = RunnerConfig::new(compat_check.target.clone());
+    config.token = token;
+    config.restricted = true;
+    let runner = ContractRunner::new(config).map_err(|e| ApiError::InternalServerError { message: e.to_string() })?;
+    let report = runner.run(&builtin_cases()).await;
+
+    let (content_type, body) = if junit {
+        ("application/xml", report.to_junit_xml())
+    } else {
+        ("application/json", report.to_json())
+    };
+    Ok(Response::builder().status(StatusCode::OK).header("content-type", content_type).body(Full::new(Bytes::from(body)))?)
+}
+
+fn parse_timestamp(query_params: &HashMap<String, String>, name: &str) -> Result<Option<DateTime<Utc>>, ApiError> {
+    query_params
+        .get(name)
+        .map(|value| {
+            DateTime::parse_from_rfc3339(value).map(|timestamp| timestamp.with_timezone(&Utc)).map_err(|_| ApiError::BadRequest {
+                message: format!("Invalid {} timestamp, expected RFC 3339: {}", name, value),
+            })
+        })
+        .transpose()
+}
@@ -0,0 +1,149 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! API key management handlers
+
+use crate::api_keys::{ApiKeyStore, CreateApiKeyRequest};
+use crate::audit::read_body;
+use crate::error::ApiError;
+use crate::middleware::extract_claims;
+use http_body_util::Full;
+use hyper::{Request, Response, StatusCode, body::Bytes};
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Create an API key; the response is the only time its secret is shown
+/// POST /api/v1/admin/api-keys
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/api-keys",
+    request_body = CreateApiKeyRequest,
+    responses(
+        (status = 201, description = "Key created, with its secret", body = crate::api_keys::CreatedApiKey),
+        (status = 400, description = "Invalid name, scopes or dots"),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "API keys are not enabled")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Admin"
+)]
+pub async fn create_api_key(req: Request<hyper::body::Incoming>, api_keys: Option<Arc<ApiKeyStore>>) -> Result<Response<Full<Bytes>>, ApiError> {
+    let owner = require_admin(&req)?;
+    let api_keys = enabled(api_keys)?;
+
+    let body = read_body(req).await?;
+    let request: CreateApiKeyRequest = serde_json::from_slice(&body).map_err(|e| ApiError::BadRequest {
+        message: format!("Invalid API key request: {}", e),
+    })?;
+    let created = api_keys.create(&owner, request).await?;
+    json_response(StatusCode::CREATED, &created)
+}
+
+/// List API keys by prefix, with their scopes and when they were created and last used
+/// GET /api/v1/admin/api-keys
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/api-keys",
+    responses(
+        (status = 200, description = "Every key, oldest first", body = [crate::api_keys::ApiKeyInfo]),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "API keys are not enabled")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Admin"
+)]
+pub async fn list_api_keys(req: Request<hyper::body::Incoming>, api_keys: Option<Arc<ApiKeyStore>>) -> Result<Response<Full<Bytes>>, ApiError> {
+    require_admin(&req)?;
+    let keys = enabled(api_keys)?.list().await?;
+    json_response(StatusCode::OK, &keys)
+}
+
+/// Replace an API key with a new secret; the old one keeps working for the rotation grace period
+/// POST /api/v1/admin/api-keys/{id}/rotate
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/api-keys/{id}/rotate",
+    params(
+        ("id" = String, Path, description = "Key to rotate")
+    ),
+    responses(
+        (status = 200, description = "Replacement key, with its secret", body = crate::api_keys::CreatedApiKey),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "Key not found or API keys are not enabled"),
+        (status = 409, description = "Key is revoked, expired or already rotated")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Admin"
+)]
+pub async fn rotate_api_key(req: Request<hyper::body::Incoming>, id: String, api_keys: Option<Arc<ApiKeyStore>>) -> Result<Response<Full<Bytes>>, ApiError> {
+    require_admin(&req)?;
+    let created = enabled(api_keys)?.rotate(&id).await?;
+    json_response(StatusCode::OK, &created)
+}
+
+/// Revoke an API key
+/// DELETE /api/v1/admin/api-keys/{id}
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/api-keys/{id}",
+    params(
+        ("id" = String, Path, description = "Key to revoke")
+    ),
+    responses(
+        (status = 200, description = "The revoked key", body = crate::api_keys::ApiKeyInfo),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "Key not found or API keys are not enabled")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Admin"
+)]
+pub async fn revoke_api_key(req: Request<hyper::body::Incoming>, id: String, api_keys: Option<Arc<ApiKeyStore>>) -> Result<Response<Full<Bytes>>, ApiError> {
+    require_admin(&req)?;
+    let key = enabled(api_keys)?.revoke(&id).await?;
+    json_response(StatusCode::OK, &key)
+}
+
+/// The caller's principal, if they are an admin
+fn require_admin(req: &Request<hyper::body::Incoming>) -> Result<String, ApiError> {
+    let claims = extract_claims(req)?;
+    if !claims.has_role("admin") {
+        return Err(ApiError::Forbidden {
+            message: "API keys are only managed by admins".to_string(),
+        });
+    }
+    Ok(claims.sub.clone())
+}
+
+fn enabled(api_keys: Option<Arc<ApiKeyStore>>) -> Result<Arc<ApiKeyStore>, ApiError> {
+    api_keys.ok_or_else(|| ApiError::NotFound {
+        message: "API keys are not enabled".to_string(),
+    })
+}
+
+fn json_response(status: StatusCode, body: &impl Serialize) -> Result<Response<Full<Bytes>>, ApiError> {
+    Ok(Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(Full::new(Bytes::from(serde_json::to_string(body)?)))?)
+}
@@ -16,11 +16,12 @@
 
 //! Authentication handlers
 
+use crate::audit::read_body;
 use crate::auth::AuthService;
 use crate::error::{ApiError, ApiResult};
 use crate::middleware::extract_claims;
 use crate::models::{LoginRequest, TokenResponse, UserProfile};
-use http_body_util::{BodyExt, Full};
+use http_body_util::Full;
 use hyper::{Request, Response, StatusCode, body::Bytes};
 use std::sync::Arc;
 use tokio::sync::Mutex;
@@ -43,7 +44,7 @@ pub async fn login(req: Request<hyper::body::Incoming>, auth_service: Arc<Mutex<
     info!("Processing login request");
 
     // Read request body
-    let body = req.into_body().collect().await?.to_bytes();
+    let body = read_body(req).await?;
     let login_request: LoginRequest = serde_json::from_slice(&body)?;
 
     // Authenticate user
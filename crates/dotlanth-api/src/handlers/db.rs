@@ -16,14 +16,21 @@
 
 //! Database handlers
 
-use crate::db::DatabaseClient;
-use crate::error::ApiError;
+use crate::audit::read_body;
+use crate::db::{DatabaseClient, ReadOptions};
+use crate::error::{ApiError, version_etag};
+use crate::handlers::transactions::transaction_id;
 use crate::middleware::{check_permissions, extract_claims};
-use crate::models::{Collection, CreateDocumentRequest, CreateDocumentResponse, Document, DocumentList, SearchResults, UpdateDocumentRequest};
-use http_body_util::{BodyExt, Full};
+use crate::models::{Collection, CreateDocumentRequest, CreateDocumentResponse, Document, DocumentList, SearchResults, SnapshotInfo, UpdateDocumentRequest};
+use crate::snapshots::SNAPSHOT_PARAM;
+use chrono::{DateTime, Utc};
+use dotdb_core::document::{AggregationSpec, Projection, ReadSnapshot};
+use http_body_util::Full;
+use hyper::header::{ETAG, IF_MATCH};
 use hyper::{Request, Response, StatusCode, body::Bytes};
 use percent_encoding::percent_decode_str;
 use std::collections::HashMap;
+use std::sync::Arc;
 use tracing::{error, info};
 
 /// List all collections
@@ -166,13 +173,19 @@ pub async fn delete_collection(req: Request<hyper::body::Incoming>, collection_n
     params(
         ("collection" = String, Path, description = "Collection name"),
         ("page" = Option<u32>, Query, description = "Page number (1-based)"),
-        ("page_size" = Option<u32>, Query, description = "Number of documents per page")
+        ("page_size" = Option<u32>, Query, description = "Number of documents per page"),
+        ("sort" = Option<String>, Query, description = "Path to order documents by, e.g. age or address.city; ties and unsorted lists are ordered by document ID"),
+        ("desc" = Option<bool>, Query, description = "Sort in descending order"),
+        ("fields" = Option<String>, Query, description = "Comma-separated paths to return, e.g. name,address.city"),
+        ("as_of" = Option<String>, Query, description = "List the collection as it was at this RFC 3339 time, e.g. 2025-06-01T12:00:00Z"),
+        ("snapshot" = Option<String>, Query, description = "List the collection through this read snapshot token")
     ),
     responses(
         (status = 200, description = "List of documents", body = DocumentList),
+        (status = 400, description = "Bad request, or both as_of and snapshot given"),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden"),
-        (status = 404, description = "Collection not found")
+        (status = 404, description = "Collection or snapshot not found, or as_of precedes the retained history")
     ),
     security(
         ("bearer_auth" = [])
@@ -185,6 +198,7 @@ pub async fn get_documents(req: Request<hyper::body::Incoming>, collection_name:
     // Check authentication and permissions
     let claims = extract_claims(&req)?;
     check_permissions(claims, &["read:documents"])?;
+    let owner = claims.sub.clone();
 
     // Decode collection name
     let collection_name = percent_decode_str(&collection_name)
@@ -205,8 +219,20 @@ pub async fn get_documents(req: Request<hyper::body::Incoming>, collection_name:
         });
     }
 
+    let sort = query_params.get("sort").filter(|sort| !sort.is_empty()).cloned();
+    let descending = match query_params.get("desc").map(String::as_str) {
+        None | Some("false") => false,
+        Some("" | "true") => true,
+        Some(other) => {
+            return Err(ApiError::BadRequest {
+                message: format!("desc must be true or false, got '{other}'"),
+            });
+        }
+    };
+    let read = parse_read(&query_params, &db_client, &owner)?;
+
     // Get documents
-    let document_list = db_client.get_documents(&collection_name, page, page_size).await?;
+    let document_list = db_client.get_documents(&collection_name, page, page_size, sort, descending, &read).await?;
 
     info!("Retrieved {} documents from collection: {}", document_list.documents.len(), collection_name);
 
@@ -224,7 +250,8 @@ pub async fn get_documents(req: Request<hyper::body::Incoming>, collection_name:
     post,
     path = "/api/v1/collections/{collection}/documents",
     params(
-        ("collection" = String, Path, description = "Collection name")
+        ("collection" = String, Path, description = "Collection name"),
+        ("X-Transaction-Id" = Option<String>, Header, description = "Run the request in this transaction")
     ),
     request_body = CreateDocumentRequest,
     responses(
@@ -232,7 +259,8 @@ pub async fn get_documents(req: Request<hyper::body::Incoming>, collection_name:
         (status = 400, description = "Bad request"),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden"),
-        (status = 404, description = "Collection not found")
+        (status = 404, description = "Collection or transaction not found"),
+        (status = 409, description = "Transaction aborted")
     ),
     security(
         ("bearer_auth" = [])
@@ -245,6 +273,8 @@ pub async fn create_document(req: Request<hyper::body::Incoming>, collection_nam
     // Check authentication and permissions
     let claims = extract_claims(&req)?;
     check_permissions(claims, &["write:documents"])?;
+    let owner = claims.sub.clone();
+    let txn_id = transaction_id(&req)?;
 
     // Decode collection name
     let collection_name = percent_decode_str(&collection_name)
@@ -255,11 +285,14 @@ pub async fn create_document(req: Request<hyper::body::Incoming>, collection_nam
         .to_string();
 
     // Read request body
-    let body = req.into_body().collect().await?.to_bytes();
+    let body = read_body(req).await?;
     let create_request: CreateDocumentRequest = serde_json::from_slice(&body)?;
 
     // Create document
-    let response = db_client.create_document(&collection_name, create_request.content).await?;
+    let response = match txn_id {
+        Some(txn_id) => db_client.create_document_in(&txn_id, &owner, &collection_name, create_request.content).await?,
+        None => db_client.create_document(&collection_name, create_request.content).await?,
+    };
 
     info!("Created document {} in collection: {}", response.id, collection_name);
 
@@ -278,25 +311,38 @@ pub async fn create_document(req: Request<hyper::body::Incoming>, collection_nam
     path = "/api/v1/collections/{collection}/documents/{id}",
     params(
         ("collection" = String, Path, description = "Collection name"),
-        ("id" = String, Path, description = "Document ID")
+        ("id" = String, Path, description = "Document ID"),
+        ("as_of" = Option<String>, Query, description = "Read the document as it was at this RFC 3339 time, e.g. 2025-06-01T12:00:00Z"),
+        ("snapshot" = Option<String>, Query, description = "Read the document through this read snapshot token"),
+        ("X-Transaction-Id" = Option<String>, Header, description = "Run the request in this transaction")
     ),
     responses(
-        (status = 200, description = "Document found", body = Document),
+        (status = 200, description = "Document found, with its version as ETag unless read as of a past time or through a snapshot", body = Document),
+        (status = 400, description = "Bad request, or more than one of as_of, snapshot and a transaction given"),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden"),
-        (status = 404, description = "Document or collection not found")
+        (status = 404, description = "Document, collection, snapshot or transaction not found, or as_of precedes the retained history"),
+        (status = 409, description = "Transaction aborted")
     ),
     security(
         ("bearer_auth" = [])
     ),
     tag = "Database"
 )]
-pub async fn get_document(req: Request<hyper::body::Incoming>, collection_name: String, document_id: String, db_client: DatabaseClient) -> Result<Response<Full<Bytes>>, ApiError> {
+pub async fn get_document(
+    req: Request<hyper::body::Incoming>,
+    collection_name: String,
+    document_id: String,
+    query_params: HashMap<String, String>,
+    db_client: DatabaseClient,
+) -> Result<Response<Full<Bytes>>, ApiError> {
     info!("Processing get document request: {}/{}", collection_name, document_id);
 
     // Check authentication and permissions
     let claims = extract_claims(&req)?;
     check_permissions(claims, &["read:documents"])?;
+    let owner = claims.sub.clone();
+    let txn_id = transaction_id(&req)?;
 
     // Decode parameters
     let collection_name = percent_decode_str(&collection_name)
@@ -314,16 +360,29 @@ pub async fn get_document(req: Request<hyper::body::Incoming>, collection_name:
         .to_string();
 
     // Get document
-    let document = db_client.get_document(&collection_name, &document_id).await?;
+    let as_of = parse_as_of(&query_params)?;
+    let snapshot = parse_snapshot(&query_params, &db_client, &owner)?;
+    let document = match (&txn_id, &snapshot) {
+        (Some(_), _) if as_of.is_some() || snapshot.is_some() => {
+            return Err(ApiError::BadRequest {
+                message: "as_of and snapshot cannot be used within a transaction".to_string(),
+            });
+        }
+        (Some(txn_id), _) => db_client.get_document_in(txn_id, &owner, &collection_name, &document_id).await?,
+        (None, Some(_)) if as_of.is_some() => return Err(as_of_with_snapshot()),
+        (None, Some(snapshot)) => db_client.get_document_in_snapshot(snapshot, &collection_name, &document_id).await?,
+        (None, None) => db_client.get_document(&collection_name, &document_id, as_of).await?,
+    };
 
     info!("Retrieved document {} from collection: {}", document_id, collection_name);
 
     let response_json = serde_json::to_string(&document)?;
 
-    Ok(Response::builder()
-        .status(StatusCode::OK)
-        .header("content-type", "application/json")
-        .body(Full::new(Bytes::from(response_json)))?)
+    let mut response = Response::builder().status(StatusCode::OK).header("content-type", "application/json");
+    if txn_id.is_none() && as_of.is_none() && snapshot.is_none() {
+        response = response.header(ETAG, version_etag(document.version));
+    }
+    Ok(response.body(Full::new(Bytes::from(response_json)))?)
 }
 
 /// Update a document
@@ -333,15 +392,19 @@ pub async fn get_document(req: Request<hyper::body::Incoming>, collection_name:
     path = "/api/v1/collections/{collection}/documents/{id}",
     params(
         ("collection" = String, Path, description = "Collection name"),
-        ("id" = String, Path, description = "Document ID")
+        ("id" = String, Path, description = "Document ID"),
+        ("X-Transaction-Id" = Option<String>, Header, description = "Run the request in this transaction"),
+        ("If-Match" = Option<String>, Header, description = "Only update the document if its ETag still matches, e.g. \"3\"")
     ),
     request_body = UpdateDocumentRequest,
     responses(
-        (status = 200, description = "Document updated", body = Document),
-        (status = 400, description = "Bad request"),
+        (status = 200, description = "Document updated, with its new version as ETag", body = Document),
+        (status = 400, description = "Bad request, or If-Match given within a transaction"),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden"),
-        (status = 404, description = "Document or collection not found")
+        (status = 404, description = "Document, collection or transaction not found"),
+        (status = 409, description = "Transaction aborted"),
+        (status = 412, description = "The document was updated since the If-Match version; the current version is sent as ETag")
     ),
     security(
         ("bearer_auth" = [])
@@ -354,6 +417,9 @@ pub async fn update_document(req: Request<hyper::body::Incoming>, collection_nam
     // Check authentication and permissions
     let claims = extract_claims(&req)?;
     check_permissions(claims, &["write:documents"])?;
+    let owner = claims.sub.clone();
+    let txn_id = transaction_id(&req)?;
+    let expected_version = if_match_version(&req, txn_id.is_some())?;
 
     // Decode parameters
     let collection_name = percent_decode_str(&collection_name)
@@ -371,11 +437,15 @@ pub async fn update_document(req: Request<hyper::body::Incoming>, collection_nam
         .to_string();
 
     // Read request body
-    let body = req.into_body().collect().await?.to_bytes();
+    let body = read_body(req).await?;
     let update_request: UpdateDocumentRequest = serde_json::from_slice(&body)?;
 
     // Update document
-    let document = db_client.update_document(&collection_name, &document_id, update_request.content).await?;
+    let document = match (txn_id, expected_version) {
+        (Some(txn_id), _) => db_client.update_document_in(&txn_id, &owner, &collection_name, &document_id, update_request.content).await?,
+        (None, Some(version)) => db_client.update_document_if_version(&collection_name, &document_id, update_request.content, version).await?,
+        (None, None) => db_client.update_document(&collection_name, &document_id, update_request.content).await?,
+    };
 
     info!("Updated document {} in collection: {}", document_id, collection_name);
 
@@ -384,6 +454,7 @@ pub async fn update_document(req: Request<hyper::body::Incoming>, collection_nam
     Ok(Response::builder()
         .status(StatusCode::OK)
         .header("content-type", "application/json")
+        .header(ETAG, version_etag(document.version))
         .body(Full::new(Bytes::from(response_json)))?)
 }
 
@@ -394,13 +465,18 @@ pub async fn update_document(req: Request<hyper::body::Incoming>, collection_nam
     path = "/api/v1/collections/{collection}/documents/{id}",
     params(
         ("collection" = String, Path, description = "Collection name"),
-        ("id" = String, Path, description = "Document ID")
+        ("id" = String, Path, description = "Document ID"),
+        ("X-Transaction-Id" = Option<String>, Header, description = "Run the request in this transaction"),
+        ("If-Match" = Option<String>, Header, description = "Only delete the document if its ETag still matches, e.g. \"3\"")
     ),
     responses(
         (status = 204, description = "Document deleted"),
+        (status = 400, description = "Bad request, or If-Match given within a transaction"),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden"),
-        (status = 404, description = "Document or collection not found")
+        (status = 404, description = "Document, collection or transaction not found"),
+        (status = 409, description = "Transaction aborted"),
+        (status = 412, description = "The document was updated since the If-Match version; the current version is sent as ETag")
     ),
     security(
         ("bearer_auth" = [])
@@ -413,6 +489,9 @@ pub async fn delete_document(req: Request<hyper::body::Incoming>, collection_nam
     // Check authentication and permissions
     let claims = extract_claims(&req)?;
     check_permissions(claims, &["delete:documents"])?;
+    let owner = claims.sub.clone();
+    let txn_id = transaction_id(&req)?;
+    let expected_version = if_match_version(&req, txn_id.is_some())?;
 
     // Decode parameters
     let collection_name = percent_decode_str(&collection_name)
@@ -430,7 +509,11 @@ pub async fn delete_document(req: Request<hyper::body::Incoming>, collection_nam
         .to_string();
 
     // Delete document
-    db_client.delete_document(&collection_name, &document_id).await?;
+    match (txn_id, expected_version) {
+        (Some(txn_id), _) => db_client.delete_document_in(&txn_id, &owner, &collection_name, &document_id).await?,
+        (None, Some(version)) => db_client.delete_document_if_version(&collection_name, &document_id, version).await?,
+        (None, None) => db_client.delete_document(&collection_name, &document_id).await?,
+    }
 
     info!("Deleted document {} from collection: {}", document_id, collection_name);
 
@@ -446,14 +529,17 @@ pub async fn delete_document(req: Request<hyper::body::Incoming>, collection_nam
         ("collection" = String, Path, description = "Collection name"),
         ("q" = String, Query, description = "Search query"),
         ("limit" = Option<u32>, Query, description = "Maximum number of results"),
-        ("offset" = Option<u32>, Query, description = "Offset for pagination")
+        ("offset" = Option<u32>, Query, description = "Offset for pagination"),
+        ("fields" = Option<String>, Query, description = "Comma-separated paths to return, e.g. name,address.city"),
+        ("as_of" = Option<String>, Query, description = "Search the collection as it was at this RFC 3339 time, e.g. 2025-06-01T12:00:00Z"),
+        ("snapshot" = Option<String>, Query, description = "Search the collection through this read snapshot token")
     ),
     responses(
         (status = 200, description = "Search results", body = SearchResults),
-        (status = 400, description = "Bad request"),
+        (status = 400, description = "Bad request, or both as_of and snapshot given"),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden"),
-        (status = 404, description = "Collection not found")
+        (status = 404, description = "Collection or snapshot not found, or as_of precedes the retained history")
     ),
     security(
         ("bearer_auth" = [])
@@ -471,6 +557,7 @@ pub async fn search_documents(
     // Check authentication and permissions
     let claims = extract_claims(&req)?;
     check_permissions(claims, &["read:documents"])?;
+    let owner = claims.sub.clone();
 
     // Decode collection name
     let collection_name = percent_decode_str(&collection_name)
@@ -498,8 +585,10 @@ pub async fn search_documents(
         }
     }
 
+    let read = parse_read(&query_params, &db_client, &owner)?;
+
     // Search documents
-    let search_results = db_client.search_documents(&collection_name, query, limit, offset).await?;
+    let search_results = db_client.search_documents(&collection_name, query, limit, offset, &read).await?;
 
     info!("Search found {} matches in collection: {} (query: {})", search_results.total_matches, collection_name, query);
 
@@ -510,3 +599,197 @@ pub async fn search_documents(
         .header("content-type", "application/json")
         .body(Full::new(Bytes::from(response_json)))?)
 }
+
+/// Aggregate the documents of a collection
+/// POST /api/v1/collections/{collection}/aggregate
+#[utoipa::path(
+    post,
+    path = "/api/v1/collections/{collection}/aggregate",
+    params(
+        ("collection" = String, Path, description = "Collection name"),
+        ("snapshot" = Option<String>, Query, description = "Aggregate the collection through this read snapshot token")
+    ),
+    request_body(content = Object, description = "Aggregation spec: group_by paths, an optional time window and the aggregates to compute, as for `dotdb aggregate`"),
+    responses(
+        (status = 200, description = "Groups with their aggregates, documents scanned and values skipped", body = Object),
+        (status = 400, description = "Invalid aggregation spec"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Collection or snapshot not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Database"
+)]
+pub async fn aggregate_documents(
+    req: Request<hyper::body::Incoming>,
+    collection_name: String,
+    query_params: HashMap<String, String>,
+    db_client: DatabaseClient,
+) -> Result<Response<Full<Bytes>>, ApiError> {
+    info!("Processing aggregate request: {}", collection_name);
+
+    // Check authentication and permissions
+    let claims = extract_claims(&req)?;
+    check_permissions(claims, &["read:documents"])?;
+    let snapshot = parse_snapshot(&query_params, &db_client, &claims.sub)?;
+
+    // Decode collection name
+    let collection_name = percent_decode_str(&collection_name)
+        .decode_utf8()
+        .map_err(|_| ApiError::BadRequest {
+            message: "Invalid collection name encoding".to_string(),
+        })?
+        .to_string();
+
+    let body = read_body(req).await?;
+    let spec: AggregationSpec = serde_json::from_slice(&body).map_err(|e| ApiError::BadRequest {
+        message: format!("Invalid aggregation spec: {e}"),
+    })?;
+
+    let result = db_client.aggregate(&collection_name, &spec, snapshot.as_deref()).await?;
+
+    let response_json = serde_json::to_string(&result)?;
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("content-type", "application/json")
+        .body(Full::new(Bytes::from(response_json)))?)
+}
+
+/// Take a read snapshot
+/// POST /api/v1/snapshots
+#[utoipa::path(
+    post,
+    path = "/api/v1/snapshots",
+    responses(
+        (status = 201, description = "Snapshot taken", body = SnapshotInfo),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Database"
+)]
+pub async fn begin_snapshot(req: Request<hyper::body::Incoming>, db_client: DatabaseClient) -> Result<Response<Full<Bytes>>, ApiError> {
+    info!("Processing begin snapshot request");
+
+    // Check authentication and permissions
+    let claims = extract_claims(&req)?;
+    check_permissions(claims, &["read:documents"])?;
+
+    let snapshot = db_client.begin_snapshot(&claims.sub).await?;
+
+    let response_json = serde_json::to_string(&snapshot)?;
+
+    Ok(Response::builder()
+        .status(StatusCode::CREATED)
+        .header("content-type", "application/json")
+        .body(Full::new(Bytes::from(response_json)))?)
+}
+
+/// Release a read snapshot
+/// DELETE /api/v1/snapshots/{token}
+#[utoipa::path(
+    delete,
+    path = "/api/v1/snapshots/{token}",
+    params(
+        ("token" = String, Path, description = "Snapshot token")
+    ),
+    responses(
+        (status = 204, description = "Snapshot released"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Snapshot not found or already released")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Database"
+)]
+pub async fn end_snapshot(req: Request<hyper::body::Incoming>, token: String, db_client: DatabaseClient) -> Result<Response<Full<Bytes>>, ApiError> {
+    info!("Processing end snapshot request: {}", token);
+
+    // Check authentication and permissions
+    let claims = extract_claims(&req)?;
+    check_permissions(claims, &["read:documents"])?;
+
+    db_client.end_snapshot(&token, &claims.sub).await?;
+
+    Ok(Response::builder().status(StatusCode::NO_CONTENT).body(Full::new(Bytes::new()))?)
+}
+
+/// How listing and search read, from the `as_of`, `snapshot` and `fields` query parameters
+fn parse_read(query_params: &HashMap<String, String>, db_client: &DatabaseClient, owner: &str) -> Result<ReadOptions, ApiError> {
+    let read = ReadOptions {
+        as_of: parse_as_of(query_params)?,
+        snapshot: parse_snapshot(query_params, db_client, owner)?,
+        fields: parse_fields(query_params)?,
+    };
+    if read.as_of.is_some() && read.snapshot.is_some() {
+        return Err(as_of_with_snapshot());
+    }
+    Ok(read)
+}
+
+/// The read snapshot of `owner` named by the `snapshot` query parameter, if there is one
+fn parse_snapshot(query_params: &HashMap<String, String>, db_client: &DatabaseClient, owner: &str) -> Result<Option<Arc<ReadSnapshot>>, ApiError> {
+    match query_params.get(SNAPSHOT_PARAM).filter(|token| !token.is_empty()) {
+        Some(token) => db_client.read_snapshot(token, owner).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn as_of_with_snapshot() -> ApiError {
+    ApiError::BadRequest {
+        message: "as_of and snapshot cannot be used together".to_string(),
+    }
+}
+
+/// The projection named by the `fields` query parameter, if there is one
+fn parse_fields(query_params: &HashMap<String, String>) -> Result<Option<Projection>, ApiError> {
+    match query_params.get("fields").filter(|fields| !fields.is_empty()) {
+        Some(fields) => Projection::parse(fields).map(Some).map_err(|e| ApiError::BadRequest { message: e.to_string() }),
+        None => Ok(None),
+    }
+}
+
+/// The time named by the `as_of` query parameter, if there is one
+fn parse_as_of(query_params: &HashMap<String, String>) -> Result<Option<DateTime<Utc>>, ApiError> {
+    match query_params.get("as_of").filter(|as_of| !as_of.is_empty()) {
+        Some(as_of) => DateTime::parse_from_rfc3339(as_of).map(|as_of| Some(as_of.with_timezone(&Utc))).map_err(|e| ApiError::BadRequest {
+            message: format!("as_of must be an RFC 3339 time such as 2025-06-01T12:00:00Z, got '{as_of}': {e}"),
+        }),
+        None => Ok(None),
+    }
+}
+
+/// The document version named by the `If-Match` header, if the write is conditional
+///
+/// `*` matches any existing document, so it sets no version. Transactions detect
+/// conflicting writes themselves and do not take a version.
+fn if_match_version<B>(req: &Request<B>, in_transaction: bool) -> Result<Option<u64>, ApiError> {
+    let Some(value) = req.headers().get(IF_MATCH) else {
+        return Ok(None);
+    };
+    if in_transaction {
+        return Err(ApiError::BadRequest {
+            message: "If-Match cannot be used within a transaction".to_string(),
+        });
+    }
+
+    let value = value.to_str().unwrap_or_default().trim();
+    if value == "*" {
+        return Ok(None);
+    }
+    value
+        .strip_prefix('"')
+        .and_then(|tag| tag.strip_suffix('"'))
+        .and_then(|version| version.parse().ok())
+        .map(Some)
+        .ok_or_else(|| ApiError::BadRequest {
+            message: format!("If-Match must be a document ETag such as \"3\", got '{value}'"),
+        })
+}
@@ -0,0 +1,278 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Gateway bridge handlers
+
+use crate::admission::{AdmissionConfig, AdmissionConfigUpdate, AdmissionController};
+use crate::audit::read_body;
+use crate::error::ApiError;
+use crate::gateway::GatewayBridge;
+use crate::middleware::extract_claims;
+use crate::rate_limiting::PriorityRateLimiter;
+use crate::response_cache::ResponseCache;
+use crate::vm::VmClient;
+use http_body_util::Full;
+use hyper::{Request, Response, StatusCode, body::Bytes};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::info;
+
+/// Gateway bridge health
+/// GET /api/v1/gateway/health
+#[utoipa::path(
+    get,
+    path = "/api/v1/gateway/health",
+    responses(
+        (status = 200, description = "Gateway bridge is healthy"),
+        (status = 503, description = "Gateway bridge is unhealthy")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Gateway"
+)]
+pub async fn gateway_health(gateway_bridge: Arc<GatewayBridge>) -> Result<Response<Full<Bytes>>, ApiError> {
+    let (status_code, response) = match gateway_bridge.health_check().await {
+        Ok(_) => (
+            StatusCode::OK,
+            serde_json::json!({
+                "status": "healthy",
+                "timestamp": chrono::Utc::now().to_rfc3339(),
+                "service": "gateway_bridge"
+            }),
+        ),
+        Err(e) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            serde_json::json!({
+                "status": "unhealthy",
+                "error": e.to_string(),
+                "timestamp": chrono::Utc::now().to_rfc3339(),
+                "service": "gateway_bridge"
+            }),
+        ),
+    };
+
+    Ok(Response::builder()
+        .status(status_code)
+        .header("content-type", "application/json")
+        .body(Full::new(Bytes::from(serde_json::to_string(&response)?)))?)
+}
+
+/// Gateway bridge request and protocol conversion metrics, plus VM channel pool state
+/// GET /api/v1/gateway/metrics
+#[utoipa::path(
+    get,
+    path = "/api/v1/gateway/metrics",
+    responses(
+        (status = 200, description = "Gateway bridge metrics")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Gateway"
+)]
+pub async fn gateway_metrics(gateway_bridge: Arc<GatewayBridge>, vm_client: &VmClient) -> Result<Response<Full<Bytes>>, ApiError> {
+    let metrics = gateway_bridge.get_metrics().await;
+
+    let response = serde_json::json!({
+        "metrics": {
+            "total_requests": metrics.total_requests,
+            "successful_requests": metrics.successful_requests,
+            "failed_requests": metrics.failed_requests,
+            "avg_latency_ms": metrics.avg_latency_ms,
+            "active_streaming_connections": metrics.active_streaming_connections,
+            "protocol_conversions": metrics.protocol_conversions,
+            "error_rate": if metrics.total_requests > 0 {
+                metrics.failed_requests as f64 / metrics.total_requests as f64
+            } else {
+                0.0
+            },
+            "success_rate": if metrics.total_requests > 0 {
+                metrics.successful_requests as f64 / metrics.total_requests as f64
+            } else {
+                0.0
+            }
+        },
+        "vm_pool": vm_client.pool_stats(),
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        "service": "gateway_bridge"
+    });
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("content-type", "application/json")
+        .body(Full::new(Bytes::from(serde_json::to_string(&response)?)))?)
+}
+
+/// Rate limiting counters per API key
+/// GET /api/v1/gateway/rate-limits
+#[utoipa::path(
+    get,
+    path = "/api/v1/gateway/rate-limits",
+    responses(
+        (status = 200, description = "Rate limiting counters per API key"),
+        (status = 404, description = "Rate limiting is not configured")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Gateway"
+)]
+pub async fn rate_limit_counters(rate_limiter: Option<Arc<PriorityRateLimiter>>) -> Result<Response<Full<Bytes>>, ApiError> {
+    let rate_limiter = rate_limiter.ok_or_else(|| ApiError::NotFound {
+        message: "Rate limiting is not configured".to_string(),
+    })?;
+
+    let response = serde_json::json!({
+        "enabled": rate_limiter.config().enabled,
+        "keys": rate_limiter.counters(),
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    });
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("content-type", "application/json")
+        .body(Full::new(Bytes::from(serde_json::to_string(&response)?)))?)
+}
+
+/// Response cache counters
+/// GET /api/v1/gateway/cache
+#[utoipa::path(
+    get,
+    path = "/api/v1/gateway/cache",
+    responses(
+        (status = 200, description = "Whether the response cache is enabled, and its hits, misses and invalidations under `counters`", body = Object)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Gateway"
+)]
+pub async fn response_cache_counters(response_cache: Arc<ResponseCache>) -> Result<Response<Full<Bytes>>, ApiError> {
+    let response = serde_json::json!({
+        "enabled": response_cache.config().enabled,
+        "counters": response_cache.counters(),
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    });
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("content-type", "application/json")
+        .body(Full::new(Bytes::from(serde_json::to_string(&response)?)))?)
+}
+
+/// Admission control state and requests shed by reason
+/// GET /api/v1/gateway/admission
+#[utoipa::path(
+    get,
+    path = "/api/v1/gateway/admission",
+    responses(
+        (status = 200, description = "Admission limits under `config`, executions running and queued, and requests shed by reason under `counters`", body = Object)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Gateway"
+)]
+pub async fn admission_counters(admission: Arc<AdmissionController>) -> Result<Response<Full<Bytes>>, ApiError> {
+    let response = serde_json::json!({
+        "config": admission.config(),
+        "counters": admission.counters(),
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    });
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("content-type", "application/json")
+        .body(Full::new(Bytes::from(serde_json::to_string(&response)?)))?)
+}
+
+/// Gateway settings adjustable at runtime
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GatewayConfigUpdate {
+    admission: Option<AdmissionConfigUpdate>,
+}
+
+/// Gateway settings adjustable at runtime
+/// GET /api/v1/gateway/config
+#[utoipa::path(
+    get,
+    path = "/api/v1/gateway/config",
+    responses(
+        (status = 200, description = "Current settings, with admission limits under `admission`", body = Object),
+        (status = 403, description = "Caller is not an admin")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Gateway"
+)]
+pub async fn get_config(req: Request<hyper::body::Incoming>, admission: Arc<AdmissionController>) -> Result<Response<Full<Bytes>>, ApiError> {
+    require_admin(&req)?;
+    config_response(admission.config())
+}
+
+/// Change gateway settings at runtime; fields left out keep their current value
+/// PUT /api/v1/gateway/config
+#[utoipa::path(
+    put,
+    path = "/api/v1/gateway/config",
+    request_body(content = Object, description = "Settings to change, e.g. `{\"admission\": {\"max_in_flight\": 32}}`"),
+    responses(
+        (status = 200, description = "Settings after the change", body = Object),
+        (status = 400, description = "Unknown or invalid setting"),
+        (status = 403, description = "Caller is not an admin")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Gateway"
+)]
+pub async fn update_config(req: Request<hyper::body::Incoming>, admission: Arc<AdmissionController>) -> Result<Response<Full<Bytes>>, ApiError> {
+    require_admin(&req)?;
+
+    let body = read_body(req).await?;
+    let update: GatewayConfigUpdate = serde_json::from_slice(&body).map_err(|e| ApiError::BadRequest {
+        message: format!("Invalid gateway config: {}", e),
+    })?;
+
+    if let Some(update) = update.admission {
+        let config = update.apply(&admission.config())?;
+        info!("Admission limits changed: {:?}", config);
+        admission.set_config(config);
+    }
+
+    config_response(admission.config())
+}
+
+fn require_admin(req: &Request<hyper::body::Incoming>) -> Result<(), ApiError> {
+    if !extract_claims(req)?.has_role("admin") {
+        return Err(ApiError::Forbidden {
+            message: "Gateway settings are only available to admins".to_string(),
+        });
+    }
+    Ok(())
+}
+
+fn config_response(admission: AdmissionConfig) -> Result<Response<Full<Bytes>>, ApiError> {
+    let response = serde_json::json!({ "admission": admission });
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("content-type", "application/json")
+        .body(Full::new(Bytes::from(serde_json::to_string(&response)?)))?)
+}
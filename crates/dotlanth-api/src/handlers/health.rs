@@ -19,6 +19,8 @@
 use crate::db::DatabaseClient;
 use crate::error::ApiError;
 use crate::models::{ApiVersion, HealthResponse, ServiceStatus};
+use crate::readiness::{ReadinessChecker, ReadinessReport};
+use crate::shutdown::Shutdown;
 use crate::vm::VmClient;
 use chrono::Utc;
 use http_body_util::Full;
@@ -97,6 +99,66 @@ pub async fn health_check(_req: Request<hyper::body::Incoming>, db_client: Datab
         .body(Full::new(Bytes::from(response_json)))?)
 }
 
+/// Readiness check handler
+/// GET /api/v1/ready
+///
+/// Fails as soon as shutdown starts so load balancers stop routing new requests
+/// here while in-flight ones drain.
+#[utoipa::path(
+    get,
+    path = "/api/v1/ready",
+    responses(
+        (status = 200, description = "Accepting traffic"),
+        (status = 503, description = "Shutting down")
+    ),
+    tag = "Health"
+)]
+pub async fn readiness_check(_req: Request<hyper::body::Incoming>, shutdown: &Shutdown) -> Result<Response<Full<Bytes>>, ApiError> {
+    let (status_code, status) = if shutdown.is_triggered() {
+        (StatusCode::SERVICE_UNAVAILABLE, "shutting_down")
+    } else {
+        (StatusCode::OK, "ready")
+    };
+
+    let response_json = serde_json::to_string(&serde_json::json!({ "status": status }))?;
+
+    Ok(Response::builder()
+        .status(status_code)
+        .header("content-type", "application/json")
+        .body(Full::new(Bytes::from(response_json)))?)
+}
+
+/// Liveness handler
+/// GET /livez
+///
+/// Answers as long as the process serves requests, without checking any
+/// dependency, so a dependency outage does not get the gateway restarted.
+pub async fn liveness_check(_req: Request<hyper::body::Incoming>) -> Result<Response<Full<Bytes>>, ApiError> {
+    let response_json = serde_json::to_string(&serde_json::json!({ "status": "alive" }))?;
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("content-type", "application/json")
+        .body(Full::new(Bytes::from(response_json)))?)
+}
+
+/// Dependency-aware readiness handler
+/// GET /readyz
+///
+/// Reports the status of each dependency. Fails when a hard dependency is down or
+/// shutdown has started; soft dependencies that are down are listed as warnings.
+pub async fn dependency_readiness(_req: Request<hyper::body::Incoming>, readiness: &ReadinessChecker, shutdown: &Shutdown) -> Result<Response<Full<Bytes>>, ApiError> {
+    let report = if shutdown.is_triggered() { ReadinessReport::shutting_down() } else { readiness.check().await };
+    let status_code = if report.status.is_ready() { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+
+    let response_json = serde_json::to_string(&report)?;
+
+    Ok(Response::builder()
+        .status(status_code)
+        .header("content-type", "application/json")
+        .body(Full::new(Bytes::from(response_json)))?)
+}
+
 /// Version information handler
 /// GET /api/v1/version
 #[utoipa::path(
@@ -116,6 +178,8 @@ pub async fn version_info(_req: Request<hyper::body::Incoming>) -> Result<Respon
         features: vec![
             "database_collections".to_string(),
             "document_management".to_string(),
+            "document_transactions".to_string(),
+            "read_snapshots".to_string(),
             "vm_dot_deployment".to_string(),
             "vm_dot_execution".to_string(),
             "jwt_authentication".to_string(),
@@ -16,9 +16,14 @@
 
 //! HTTP handlers for the REST API
 
+pub mod abi_validation;
+pub mod admin;
+pub mod api_keys;
 pub mod auth;
 pub mod db;
+pub mod gateway;
 pub mod health;
+pub mod transactions;
 pub mod versioning;
 pub mod vm;
 pub mod websocket;
@@ -0,0 +1,144 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Transaction handlers
+//!
+//! See the [`transactions`](crate::transactions) module for how document requests
+//! join a transaction and the errors that abort one.
+
+use crate::audit::read_body;
+use crate::db::DatabaseClient;
+use crate::error::{ApiError, ApiResult};
+use crate::middleware::{check_permissions, extract_claims};
+use crate::models::{BeginTransactionRequest, TransactionInfo};
+use crate::transactions::TRANSACTION_HEADER;
+use http_body_util::Full;
+use hyper::{Request, Response, StatusCode, body::Bytes};
+use tracing::info;
+
+/// Begin a transaction
+/// POST /api/v1/transactions
+#[utoipa::path(
+    post,
+    path = "/api/v1/transactions",
+    request_body(content = Option<BeginTransactionRequest>, description = "Isolation level; the body may be omitted"),
+    responses(
+        (status = 201, description = "Transaction begun", body = TransactionInfo),
+        (status = 400, description = "Bad request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Database"
+)]
+pub async fn begin_transaction(req: Request<hyper::body::Incoming>, db_client: DatabaseClient) -> Result<Response<Full<Bytes>>, ApiError> {
+    info!("Processing begin transaction request");
+
+    // Check authentication and permissions
+    let claims = extract_claims(&req)?;
+    check_permissions(claims, &["write:documents"])?;
+    let owner = claims.sub.clone();
+
+    // An empty body begins a serializable transaction
+    let body = read_body(req).await?;
+    let begin_request: BeginTransactionRequest = if body.is_empty() { BeginTransactionRequest::default() } else { serde_json::from_slice(&body)? };
+
+    let transaction = db_client.begin_transaction(&owner, begin_request.isolation).await?;
+
+    let response_json = serde_json::to_string(&transaction)?;
+
+    Ok(Response::builder()
+        .status(StatusCode::CREATED)
+        .header("content-type", "application/json")
+        .body(Full::new(Bytes::from(response_json)))?)
+}
+
+/// Commit a transaction
+/// POST /api/v1/transactions/{id}/commit
+#[utoipa::path(
+    post,
+    path = "/api/v1/transactions/{id}/commit",
+    params(
+        ("id" = String, Path, description = "Transaction ID")
+    ),
+    responses(
+        (status = 204, description = "Transaction committed"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Transaction not found or already finished"),
+        (status = 409, description = "Transaction aborted; the problem's code and retryable members say why")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Database"
+)]
+pub async fn commit_transaction(req: Request<hyper::body::Incoming>, transaction_id: String, db_client: DatabaseClient) -> Result<Response<Full<Bytes>>, ApiError> {
+    info!("Processing commit transaction request: {}", transaction_id);
+
+    // Check authentication and permissions
+    let claims = extract_claims(&req)?;
+    check_permissions(claims, &["write:documents"])?;
+
+    db_client.commit_transaction(&transaction_id, &claims.sub).await?;
+
+    Ok(Response::builder().status(StatusCode::NO_CONTENT).body(Full::new(Bytes::new()))?)
+}
+
+/// Abort a transaction
+/// DELETE /api/v1/transactions/{id}
+#[utoipa::path(
+    delete,
+    path = "/api/v1/transactions/{id}",
+    params(
+        ("id" = String, Path, description = "Transaction ID")
+    ),
+    responses(
+        (status = 204, description = "Transaction aborted"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Transaction not found or already finished")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Database"
+)]
+pub async fn abort_transaction(req: Request<hyper::body::Incoming>, transaction_id: String, db_client: DatabaseClient) -> Result<Response<Full<Bytes>>, ApiError> {
+    info!("Processing abort transaction request: {}", transaction_id);
+
+    // Check authentication and permissions
+    let claims = extract_claims(&req)?;
+    check_permissions(claims, &["write:documents"])?;
+
+    db_client.abort_transaction(&transaction_id, &claims.sub).await?;
+
+    Ok(Response::builder().status(StatusCode::NO_CONTENT).body(Full::new(Bytes::new()))?)
+}
+
+/// Transaction a document request joins through the `X-Transaction-Id` header, if any
+pub fn transaction_id<B>(req: &Request<B>) -> ApiResult<Option<String>> {
+    req.headers()
+        .get(TRANSACTION_HEADER)
+        .map(|value| {
+            value.to_str().map(|id| id.trim().to_string()).map_err(|_| ApiError::BadRequest {
+                message: "Invalid X-Transaction-Id header".to_string(),
+            })
+        })
+        .transpose()
+}
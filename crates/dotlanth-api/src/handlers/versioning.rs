@@ -4,11 +4,13 @@
 //! Versioning-related HTTP handlers
 
 use crate::error::{ApiError, ApiResult};
-use crate::versioning::{ApiVersion, ProtocolType, ServiceType, VersionRegistry};
-use hyper::{Method, Request, Response, body::Incoming};
+use crate::versioning::{ApiVersion, ApiVersions, ProtocolType, ServiceType, VersionRegistry};
+use http_body_util::Full;
+use hyper::{Method, Request, Response, StatusCode, body::Incoming};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::collections::HashMap;
+use std::sync::Arc;
 
 /// Version information response
 #[derive(Debug, Serialize, Deserialize)]
@@ -323,3 +325,11 @@ pub async fn handle_versioned_health(_req: Request<Incoming>, version_registry:
             message: format!("Failed to build response: {}", e),
         })
 }
+
+/// List the REST API's path versions with their lifecycle status and request counts
+pub async fn list_versions(api_versions: Arc<ApiVersions>) -> Result<Response<Full<hyper::body::Bytes>>, ApiError> {
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("content-type", "application/json")
+        .body(Full::new(hyper::body::Bytes::from(serde_json::to_string(&api_versions.discovery())?)))?)
+}
@@ -16,26 +16,49 @@
 
 //! VM handlers
 
+use crate::admission::AdmissionController;
+use crate::audit::{BodyDigest, read_body};
 use crate::error::ApiError;
+use crate::handlers::abi_validation::{self, AbiCache};
 use crate::middleware::{check_permissions, extract_claims};
-use crate::models::{DeployDotRequest, DeployDotResponse, DotState, ExecuteDotRequest, ExecuteDotResponse};
+use crate::models::{DeployDotRequest, DeployDotResponse, DeployMetadata, DeploymentStatus, DotEvent, DotState, ExecuteDotRequest, ExecuteDotResponse, PayloadFilter, StateDiff};
+use crate::router::RouterBody;
+use crate::shutdown::Shutdown;
+use crate::sse::{HEARTBEAT_INTERVAL, dot_event_stream};
+use crate::uploads::{self, DeploymentTracker, MAX_METADATA_BYTES, MultipartUpload, UPLOAD_ID_HEADER, UploadConfig, multipart_boundary};
 use crate::vm::VmClient;
-use http_body_util::{BodyExt, Full};
-use hyper::{Request, Response, StatusCode, body::Bytes};
+use futures::Stream;
+use http_body_util::Full;
+use hyper::body::{Body, Bytes};
+use hyper::header::CONTENT_LENGTH;
+use hyper::{Request, Response, StatusCode};
 use percent_encoding::percent_decode_str;
-use tracing::{error, info};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::Arc;
+use tracing::{error, info, warn};
 
 /// Deploy a new dot
 /// POST /api/v1/vm/dots/deploy
+///
+/// Large bytecode can be sent as `multipart/form-data` instead: a `metadata` part with the
+/// JSON of a `DeployMetadata`, then a `bytecode` part with the raw bytecode, which is streamed
+/// to the runtime as it arrives. Naming the upload in `X-Upload-Id` lets its progress be
+/// polled at `/api/v1/vm/deployments/{upload_id}`.
 #[utoipa::path(
     post,
     path = "/api/v1/vm/dots/deploy",
     request_body = DeployDotRequest,
+    params(
+        ("X-Upload-Id" = Option<String>, Header, description = "Name of a multipart upload, for polling its progress")
+    ),
     responses(
         (status = 201, description = "Dot deployed successfully", body = DeployDotResponse),
         (status = 400, description = "Bad request"),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden"),
+        (status = 409, description = "An upload with the same ID is in progress"),
+        (status = 413, description = "Bytecode exceeds the deployment size limit"),
         (status = 422, description = "Bytecode validation failed")
     ),
     security(
@@ -43,38 +66,85 @@ use tracing::{error, info};
     ),
     tag = "Virtual Machine"
 )]
-pub async fn deploy_dot(req: Request<hyper::body::Incoming>, vm_client: VmClient) -> Result<Response<Full<Bytes>>, ApiError> {
+pub async fn deploy_dot(
+    req: Request<hyper::body::Incoming>,
+    vm_client: VmClient,
+    abi_cache: Arc<AbiCache>,
+    deployments: Arc<DeploymentTracker>,
+    upload_config: UploadConfig,
+) -> Result<Response<Full<Bytes>>, ApiError> {
     info!("Processing deploy dot request");
 
     // Check authentication and permissions
     let claims = extract_claims(&req)?;
     check_permissions(claims, &["deploy:dots"])?;
 
+    if let Some(boundary) = multipart_boundary(req.headers()) {
+        return deploy_dot_multipart(req, &boundary, vm_client, abi_cache, deployments, upload_config).await;
+    }
+
     // Read request body
-    let body = req.into_body().collect().await?.to_bytes();
+    let body = read_body(req).await?;
     let deploy_request: DeployDotRequest = serde_json::from_slice(&body)?;
 
     // Validate request
-    if deploy_request.name.is_empty() {
+    validate_dot_name(&deploy_request.name)?;
+
+    if deploy_request.bytecode.is_empty() {
         return Err(ApiError::BadRequest {
-            message: "Dot name cannot be empty".to_string(),
+            message: "Bytecode cannot be empty".to_string(),
         });
     }
 
-    if deploy_request.name.len() > 64 {
+    // Deploy the dot
+    let response = vm_client.deploy_dot(deploy_request).await?;
+    abi_cache.invalidate(&response.dot_id);
+
+    info!("Deployed dot successfully: {}", response.dot_id);
+
+    let response_json = serde_json::to_string(&response)?;
+
+    Ok(Response::builder()
+        .status(StatusCode::CREATED)
+        .header("content-type", "application/json")
+        .body(Full::new(Bytes::from(response_json)))?)
+}
+
+/// Deploy a dot from a multipart upload without buffering its bytecode
+async fn deploy_dot_multipart(
+    req: Request<hyper::body::Incoming>,
+    boundary: &str,
+    vm_client: VmClient,
+    abi_cache: Arc<AbiCache>,
+    deployments: Arc<DeploymentTracker>,
+    upload_config: UploadConfig,
+) -> Result<Response<Full<Bytes>>, ApiError> {
+    let upload_id = uploads::upload_id(req.headers())?;
+    let expected_bytes = req.headers().get(CONTENT_LENGTH).and_then(|value| value.to_str().ok()).and_then(|value| value.parse().ok());
+    let (parts, body) = req.into_parts();
+    let mut upload = MultipartUpload::new(body, boundary, upload_config.chunk_size);
+
+    if upload.next_part().await?.as_deref() != Some("metadata") {
         return Err(ApiError::BadRequest {
-            message: "Dot name cannot exceed 64 characters".to_string(),
+            message: "A multipart deployment must start with a metadata part".to_string(),
         });
     }
-
-    if deploy_request.bytecode.is_empty() {
+    let metadata: DeployMetadata = serde_json::from_slice(&upload.read_part(MAX_METADATA_BYTES).await?)?;
+    validate_dot_name(&metadata.name)?;
+    if upload.next_part().await?.as_deref() != Some("bytecode") {
         return Err(ApiError::BadRequest {
-            message: "Bytecode cannot be empty".to_string(),
+            message: "The metadata part must be followed by a bytecode part".to_string(),
         });
     }
 
-    // Deploy the dot
-    let response = vm_client.deploy_dot(deploy_request).await?;
+    deployments.begin(&upload_id, &metadata.name, expected_bytes)?;
+    info!("Streaming upload {} of dot {} to the runtime", upload_id, metadata.name);
+
+    let bytecode = bytecode_stream(upload, upload_id.clone(), deployments.clone(), upload_config.max_bytes, parts.extensions.get::<BodyDigest>().cloned());
+    let result = vm_client.deploy_dot_stream(&metadata, bytecode).await;
+    deployments.finish(&upload_id, result.as_ref().map(|response| response.dot_id.as_str()));
+    let response = result.inspect_err(|e| warn!("Streamed upload {} failed: {}", upload_id, e))?;
+    abi_cache.invalidate(&response.dot_id);
 
     info!("Deployed dot successfully: {}", response.dot_id);
 
@@ -83,6 +153,105 @@ pub async fn deploy_dot(req: Request<hyper::body::Incoming>, vm_client: VmClient
     Ok(Response::builder()
         .status(StatusCode::CREATED)
         .header("content-type", "application/json")
+        .header(UPLOAD_ID_HEADER, upload_id)
+        .body(Full::new(Bytes::from(response_json)))?)
+}
+
+/// Chunks of the bytecode part, ending with an error once they outgrow `max_bytes` or the
+/// body turns out to be incomplete
+fn bytecode_stream<B>(
+    upload: MultipartUpload<B>,
+    upload_id: String,
+    deployments: Arc<DeploymentTracker>,
+    max_bytes: u64,
+    digest: Option<BodyDigest>,
+) -> impl Stream<Item = Result<Bytes, ApiError>> + Send + 'static
+where
+    B: Body<Data = Bytes> + Unpin + Send + 'static,
+    B::Error: Display,
+{
+    futures::stream::try_unfold((upload, 0u64), move |(mut upload, received)| {
+        let (upload_id, deployments, digest) = (upload_id.clone(), deployments.clone(), digest.clone());
+        async move {
+            let Some(chunk) = upload.next_chunk().await? else {
+                // Reading the closing boundary proves the body arrived whole
+                if upload.next_part().await?.is_some() {
+                    return Err(ApiError::BadRequest {
+                        message: "The bytecode part must be the last part".to_string(),
+                    });
+                }
+                if received == 0 {
+                    return Err(ApiError::BadRequest {
+                        message: "Bytecode cannot be empty".to_string(),
+                    });
+                }
+                if let Some(digest) = digest {
+                    digest.record_digest(upload.body_digest());
+                }
+                deployments.mark_deploying(&upload_id);
+                return Ok(None);
+            };
+
+            let received = received + chunk.len() as u64;
+            if received > max_bytes {
+                return Err(ApiError::PayloadTooLarge {
+                    message: format!("Bytecode exceeds the {} byte limit", max_bytes),
+                });
+            }
+            deployments.record_progress(&upload_id, received);
+            Ok(Some((chunk, (upload, received))))
+        }
+    })
+}
+
+fn validate_dot_name(name: &str) -> Result<(), ApiError> {
+    if name.is_empty() {
+        return Err(ApiError::BadRequest {
+            message: "Dot name cannot be empty".to_string(),
+        });
+    }
+
+    if name.len() > 64 {
+        return Err(ApiError::BadRequest {
+            message: "Dot name cannot exceed 64 characters".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Get the progress of a streamed deployment
+/// GET /api/v1/vm/deployments/{upload_id}
+#[utoipa::path(
+    get,
+    path = "/api/v1/vm/deployments/{upload_id}",
+    params(
+        ("upload_id" = String, Path, description = "Upload ID given in the X-Upload-Id header of the deployment")
+    ),
+    responses(
+        (status = 200, description = "Deployment progress", body = DeploymentStatus),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "No recent deployment with this upload ID")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Virtual Machine"
+)]
+pub async fn get_deployment_status(req: Request<hyper::body::Incoming>, upload_id: String, deployments: Arc<DeploymentTracker>) -> Result<Response<Full<Bytes>>, ApiError> {
+    let claims = extract_claims(&req)?;
+    check_permissions(claims, &["deploy:dots"])?;
+
+    let status = deployments.get(&upload_id).ok_or_else(|| ApiError::NotFound {
+        message: format!("No recent deployment with upload ID {}", upload_id),
+    })?;
+
+    let response_json = serde_json::to_string(&status)?;
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("content-type", "application/json")
         .body(Full::new(Bytes::from(response_json)))?)
 }
 
@@ -133,29 +302,106 @@ pub async fn get_dot_state(req: Request<hyper::body::Incoming>, dot_id: String,
         .body(Full::new(Bytes::from(response_json)))?)
 }
 
+/// Get the changes to a dot's state between two versions
+/// GET /api/v1/vm/dots/{id}/state/diff
+#[utoipa::path(
+    get,
+    path = "/api/v1/vm/dots/{id}/state/diff",
+    params(
+        ("id" = String, Path, description = "Dot ID"),
+        ("from" = u64, Query, description = "Version to diff from"),
+        ("to" = u64, Query, description = "Version to diff to"),
+        ("start_after" = Option<String>, Query, description = "Resume after this key (the previous page's next_key)"),
+        ("limit" = Option<u32>, Query, description = "Maximum changes per page"),
+        ("max_value_size" = Option<u64>, Query, description = "Return values larger than this many bytes as hashes")
+    ),
+    responses(
+        (status = 200, description = "State changes", body = StateDiff),
+        (status = 400, description = "Missing or invalid versions"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Dot or version not found"),
+        (status = 409, description = "Version has been pruned")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Virtual Machine"
+)]
+pub async fn get_state_diff(req: Request<hyper::body::Incoming>, dot_id: String, query_params: HashMap<String, String>, vm_client: VmClient) -> Result<Response<Full<Bytes>>, ApiError> {
+    info!("Processing state diff request: {}", dot_id);
+
+    // Check authentication and permissions
+    let claims = extract_claims(&req)?;
+    check_permissions(claims, &["execute:dots"])?;
+
+    // Decode dot ID
+    let dot_id = percent_decode_str(&dot_id)
+        .decode_utf8()
+        .map_err(|_| ApiError::BadRequest {
+            message: "Invalid dot ID encoding".to_string(),
+        })?
+        .to_string();
+
+    // Parse query parameters
+    let version = |name: &str| -> Result<u64, ApiError> {
+        let value = query_params.get(name).ok_or_else(|| ApiError::BadRequest {
+            message: format!("Missing '{}' version", name),
+        })?;
+        value.parse().map_err(|_| ApiError::BadRequest {
+            message: format!("Invalid '{}' version: {}", name, value),
+        })
+    };
+    let from = version("from")?;
+    let to = version("to")?;
+    let start_after = query_params.get("start_after").filter(|key| !key.is_empty()).cloned();
+    let limit = query_params.get("limit").and_then(|l| l.parse().ok()).unwrap_or(0);
+    let max_value_size = query_params.get("max_value_size").and_then(|s| s.parse().ok()).unwrap_or(0);
+
+    let diff = vm_client.get_state_diff(&dot_id, from, to, start_after, limit, max_value_size).await?;
+
+    info!("Retrieved {} state changes for dot: {}", diff.changes.len(), dot_id);
+
+    let response_json = serde_json::to_string(&diff)?;
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("content-type", "application/json")
+        .body(Full::new(Bytes::from(response_json)))?)
+}
+
 /// Execute a dot function
 /// POST /api/v1/vm/dots/{id}/execute
 #[utoipa::path(
     post,
     path = "/api/v1/vm/dots/{id}/execute",
     params(
-        ("id" = String, Path, description = "Dot ID")
+        ("id" = String, Path, description = "Dot ID"),
+        ("x-dotlanth-skip-validation" = Option<bool>, Header, description = "Skip ABI input validation (for debugging)"),
+        ("x-dotlanth-timeout-ms" = Option<u64>, Header, description = "How long the client waits for the execution; requests that could not start in time are rejected instead of queued")
     ),
     request_body = ExecuteDotRequest,
     responses(
         (status = 200, description = "Execution completed", body = ExecuteDotResponse),
-        (status = 400, description = "Bad request"),
+        (status = 400, description = "Bad request, or inputs do not match the dot's ABI (per-field errors under `errors`)"),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden"),
         (status = 404, description = "Dot not found"),
-        (status = 408, description = "Execution timeout")
+        (status = 408, description = "Execution timeout"),
+        (status = 429, description = "Execution shed by admission control; `reason` is `concurrency_limit`, `queue_full` or `deadline_unreachable`, with `Retry-After` set")
     ),
     security(
         ("bearer_auth" = [])
     ),
     tag = "Virtual Machine"
 )]
-pub async fn execute_dot(req: Request<hyper::body::Incoming>, dot_id: String, vm_client: VmClient) -> Result<Response<Full<Bytes>>, ApiError> {
+pub async fn execute_dot(
+    req: Request<hyper::body::Incoming>,
+    dot_id: String,
+    vm_client: VmClient,
+    abi_cache: Arc<AbiCache>,
+    admission: Arc<AdmissionController>,
+) -> Result<Response<Full<Bytes>>, ApiError> {
     info!("Processing execute dot request: {}", dot_id);
 
     // Check authentication and permissions
@@ -170,8 +416,18 @@ pub async fn execute_dot(req: Request<hyper::body::Incoming>, dot_id: String, vm
         })?
         .to_string();
 
+    // Wait for an execution slot, or give up early if none frees up in time;
+    // the permit is held until the execution finishes
+    let timeout = admission.timeout(req.headers())?;
+    let _permit = admission.admit(&dot_id, timeout).await.map_err(|shed| {
+        warn!("Shed execution of dot {} with a {} ms deadline: {}", dot_id, timeout.as_millis(), shed.reason.as_str());
+        shed.to_error(&dot_id)
+    })?;
+
+    let skip_validation = abi_validation::skip_requested(req.headers());
+
     // Read request body
-    let body = req.into_body().collect().await?.to_bytes();
+    let body = read_body(req).await?;
     let execute_request: ExecuteDotRequest = serde_json::from_slice(&body)?;
 
     // Validate request
@@ -181,6 +437,16 @@ pub async fn execute_dot(req: Request<hyper::body::Incoming>, dot_id: String, vm
         });
     }
 
+    // Check inputs against the dot's ABI before touching the runtime
+    if skip_validation {
+        warn!("ABI validation skipped by request header for dot: {}", dot_id);
+    } else if let Some(schema) = abi_cache.get(&dot_id, &vm_client).await? {
+        let errors = abi_validation::validate_execute_request(&schema, &execute_request, abi_cache.config().strict);
+        if !errors.is_empty() {
+            return Err(ApiError::InputValidation { dot_id, errors });
+        }
+    }
+
     // Execute the dot function
     let response = vm_client.execute_dot(&dot_id, execute_request).await?;
 
@@ -248,7 +514,7 @@ pub async fn list_dots(req: Request<hyper::body::Incoming>, vm_client: VmClient)
     ),
     tag = "Virtual Machine"
 )]
-pub async fn delete_dot(req: Request<hyper::body::Incoming>, dot_id: String, vm_client: VmClient) -> Result<Response<Full<Bytes>>, ApiError> {
+pub async fn delete_dot(req: Request<hyper::body::Incoming>, dot_id: String, vm_client: VmClient, abi_cache: Arc<AbiCache>) -> Result<Response<Full<Bytes>>, ApiError> {
     info!("Processing delete dot request: {}", dot_id);
 
     // Check authentication and permissions
@@ -265,6 +531,7 @@ pub async fn delete_dot(req: Request<hyper::body::Incoming>, dot_id: String, vm_
 
     // Delete the dot
     vm_client.delete_dot(&dot_id).await?;
+    abi_cache.invalidate(&dot_id);
 
     info!("Deleted dot: {}", dot_id);
 
@@ -340,3 +607,77 @@ pub async fn get_architectures(req: Request<hyper::body::Incoming>, vm_client: V
         .header("content-type", "application/json")
         .body(Full::new(Bytes::from(response_json)))?)
 }
+
+/// Stream dot events as server-sent events
+/// GET /api/v1/vm/dots/{id}/events
+#[utoipa::path(
+    get,
+    path = "/api/v1/vm/dots/{id}/events",
+    params(
+        ("id" = String, Path, description = "Dot ID"),
+        ("types" = Option<String>, Query, description = "Comma-separated event types to receive"),
+        ("where.{field}" = Option<String>, Query, description = "Only events whose JSON payload has this value at the dotted field path, e.g. where.order.status=shipped; may be repeated for different fields"),
+        ("last_event_id" = Option<String>, Query, description = "Resume after this event id (alternative to the Last-Event-ID header)")
+    ),
+    responses(
+        (status = 200, description = "Event stream (text/event-stream)", body = DotEvent, content_type = "text/event-stream"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Virtual Machine"
+)]
+pub async fn stream_dot_events(req: Request<hyper::body::Incoming>, dot_id: String, query_params: HashMap<String, String>, vm_client: VmClient, shutdown: Shutdown) -> Result<Response<RouterBody>, ApiError> {
+    info!("Processing dot event stream request: {}", dot_id);
+
+    // Check authentication and permissions
+    let claims = extract_claims(&req)?;
+    check_permissions(claims, &["execute:dots"])?;
+
+    // Decode dot ID
+    let dot_id = percent_decode_str(&dot_id)
+        .decode_utf8()
+        .map_err(|_| ApiError::BadRequest {
+            message: "Invalid dot ID encoding".to_string(),
+        })?
+        .to_string();
+
+    // Event type filter is forwarded to the runtime rather than applied here
+    let event_types: Vec<String> = query_params
+        .get("types")
+        .map(|types| types.split(',').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect())
+        .unwrap_or_default();
+
+    // Payload predicates arrive as `where.<field>=<value>` and are also applied by the runtime
+    let payload_filters: Vec<PayloadFilter> = query_params
+        .iter()
+        .filter_map(|(key, value)| {
+            key.strip_prefix("where.").filter(|field| !field.is_empty()).map(|field| PayloadFilter {
+                field: field.to_string(),
+                equals: value.clone(),
+            })
+        })
+        .collect();
+
+    // Browsers send Last-Event-ID on reconnect; the query parameter covers clients that cannot set headers
+    let last_event_id = req
+        .headers()
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+        .or_else(|| query_params.get("last_event_id").cloned())
+        .filter(|v| !v.is_empty());
+
+    let events = vm_client.stream_dot_events(&dot_id, event_types, payload_filters, last_event_id).await?;
+
+    info!("Event stream opened for dot: {}", dot_id);
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("content-type", "text/event-stream")
+        .header("cache-control", "no-cache")
+        .header("x-accel-buffering", "no")
+        .body(dot_event_stream(dot_id, events, HEARTBEAT_INTERVAL, shutdown))?)
+}
@@ -19,20 +19,35 @@
 //! This crate provides a REST API gateway that integrates with DotVM and DotDB
 //! through gRPC services, offering HTTP/REST endpoints for web clients.
 
+pub mod admission;
+pub mod api_keys;
+pub mod audit;
 pub mod auth;
 pub mod compatibility_testing;
 pub mod config;
+pub mod cors;
 pub mod db;
 pub mod error;
 pub mod gateway;
 pub mod graphql;
+pub mod grpc_pool;
 pub mod handlers;
 pub mod middleware;
 pub mod models;
+pub mod openapi;
 pub mod rate_limiting;
+pub mod readiness;
+pub mod response_cache;
 pub mod router;
 pub mod security;
 pub mod server;
+pub mod shutdown;
+pub mod snapshots;
+pub mod sse;
+pub mod telemetry;
+pub mod transactions;
+pub mod uploads;
 pub mod versioning;
 pub mod vm;
 pub mod websocket;
+pub mod websocket_mux;
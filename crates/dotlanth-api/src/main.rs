@@ -15,13 +15,16 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use dotlanth_api::{config::Config, server::ApiServer};
+use dotvm_common::log_capture::log_capture;
+use dotvm_common::telemetry::init_tracing_with_capture;
 use tracing::{error, info};
-use tracing_subscriber;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize tracing
-    tracing_subscriber::fmt::init();
+    // Initialize tracing, exporting spans over OTLP when OTEL_EXPORTER_OTLP_ENDPOINT is set and
+    // capturing events for the runtime's centralized log store
+    let (log_capture, log_events) = log_capture("dotlanth-api", 10_000);
+    let _tracing = init_tracing_with_capture("dotlanth-api", Some(log_capture))?;
 
     info!("Starting Dotlanth REST API Gateway");
 
@@ -30,7 +33,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("Loaded configuration: bind_address={}", config.bind_address);
 
     // Create and start the API server
-    let server = ApiServer::new(config).await?;
+    let server = ApiServer::new(config).await?.with_log_forwarding(log_events);
     info!("REST API Gateway started on http://{}", server.bind_address());
 
     // Start the server
@@ -42,6 +42,8 @@ impl<S> AuthMiddleware<S> {
         let public_paths = vec![
             "/api/v1/health".to_string(),
             "/api/v1/version".to_string(),
+            "/livez".to_string(),
+            "/readyz".to_string(),
             "/api/v1/auth/login".to_string(),
             "/docs".to_string(),
             "/docs/".to_string(),
@@ -83,6 +83,10 @@ pub struct Document {
     /// Document content as JSON
     pub content: serde_json::Value,
 
+    /// Requested fields the document does not have, when the query selected `fields`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing_fields: Vec<String>,
+
     /// Document creation time
     pub created_at: DateTime<Utc>,
 
@@ -197,6 +201,58 @@ pub struct SearchResults {
     pub execution_time_ms: u64,
 }
 
+/// Isolation level a transaction runs at
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionIsolation {
+    /// Reads see writes other transactions have not committed yet
+    ReadUncommitted,
+    /// Reads only see committed documents
+    ReadCommitted,
+    /// Documents read keep the version first read, and committing over a newer version fails
+    RepeatableRead,
+    /// Reads lock documents until commit, ruling out write skew
+    #[default]
+    Serializable,
+}
+
+/// Request to begin a transaction
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct BeginTransactionRequest {
+    /// Isolation level, serializable if omitted
+    #[serde(default)]
+    pub isolation: TransactionIsolation,
+}
+
+/// Transaction begun through the REST API
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TransactionInfo {
+    /// Token document requests pass in the `X-Transaction-Id` header to join the transaction
+    pub id: String,
+
+    /// Isolation level the transaction runs at
+    pub isolation: TransactionIsolation,
+
+    /// Time after which the transaction is aborted if not committed
+    pub expires_at: DateTime<Utc>,
+
+    /// Document operations the transaction may run
+    pub max_operations: usize,
+}
+
+/// Read snapshot taken through the REST API
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SnapshotInfo {
+    /// Token document reads pass in the `snapshot` query parameter to read through the snapshot
+    pub token: String,
+
+    /// Time the snapshot shows the documents as of
+    pub snapshot_at: DateTime<Utc>,
+
+    /// Time after which the snapshot is released if not ended
+    pub expires_at: DateTime<Utc>,
+}
+
 // ====== VM Models ======
 
 /// Dot deployment request
@@ -245,6 +301,70 @@ pub struct DeployDotResponse {
 
     /// Validation results
     pub validation: ValidationResult,
+
+    /// Architecture the deployed bytecode targets, e.g. `arch64`
+    pub architecture: String,
+}
+
+/// Metadata part of a multipart dot deployment; the bytecode follows in its own part
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DeployMetadata {
+    /// Dot name/identifier
+    pub name: String,
+
+    /// ABI specification
+    pub abi: Option<serde_json::Value>,
+
+    /// Deployment configuration
+    pub config: Option<DotConfig>,
+}
+
+/// Progress of a streamed dot deployment
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DeploymentStatus {
+    /// Upload ID given in the `X-Upload-Id` header of the deployment
+    pub upload_id: String,
+
+    /// Name of the dot being deployed
+    pub dot_name: String,
+
+    /// Where the deployment is at
+    pub state: DeploymentState,
+
+    /// Bytecode bytes received so far
+    pub bytes_received: u64,
+
+    /// Size of the whole request body, when the client declared it
+    pub expected_bytes: Option<u64>,
+
+    /// Deployed dot ID, once deployed
+    pub dot_id: Option<String>,
+
+    /// Why the deployment failed
+    pub error: Option<String>,
+
+    /// When the upload started
+    pub started_at: DateTime<Utc>,
+
+    /// Last change of state or progress
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Stage of a streamed dot deployment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeploymentState {
+    /// Bytecode is being received and forwarded to the runtime
+    Uploading,
+
+    /// All bytecode was received and the runtime is deploying it
+    Deploying,
+
+    /// The dot was deployed
+    Deployed,
+
+    /// The upload was interrupted or the deployment failed; nothing was deployed
+    Failed,
 }
 
 /// Dot execution request
@@ -253,9 +373,13 @@ pub struct ExecuteDotRequest {
     /// Function name to execute
     pub function: String,
 
-    /// Function arguments
+    /// Function arguments, bound to the dot's ABI inputs in declaration order
     pub arguments: Vec<serde_json::Value>,
 
+    /// Named inputs, validated against the dot's ABI
+    #[serde(default)]
+    pub inputs: HashMap<String, serde_json::Value>,
+
     /// Execution context
     pub context: Option<ExecutionContext>,
 }
@@ -309,6 +433,73 @@ pub struct DotState {
 
     /// Version number
     pub version: u64,
+
+    /// Architecture the dot's active version targets, reported when listing dots
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub architecture: Option<String>,
+}
+
+/// Changes to a dot's state between two versions
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct StateDiff {
+    /// Dot ID
+    pub dot_id: String,
+
+    /// Version the diff starts from
+    pub from_version: u64,
+
+    /// Version the diff ends at
+    pub to_version: u64,
+
+    /// State root hash of the from version
+    pub from_root_hash: String,
+
+    /// State root hash of the to version
+    pub to_root_hash: String,
+
+    /// Changed keys in key order
+    pub changes: Vec<StateChange>,
+
+    /// Pass as `start_after` to fetch the next page, absent on the last page
+    pub next_key: Option<String>,
+}
+
+/// A key that changed between two versions
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct StateChange {
+    /// State key
+    pub key: String,
+
+    /// Kind of change
+    pub kind: StateChangeKind,
+
+    /// Value in the from version, absent for added keys
+    pub old_value: Option<StateValue>,
+
+    /// Value in the to version, absent for removed keys
+    pub new_value: Option<StateValue>,
+}
+
+/// Kind of state change
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StateChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// A value in a state diff; large values carry only their hash
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct StateValue {
+    /// Value as base64, absent when replaced by its hash
+    pub value: Option<String>,
+
+    /// Hex SHA-256 of the value, present when it exceeded `max_value_size`
+    pub sha256: Option<String>,
+
+    /// Value size in bytes
+    pub size: u64,
 }
 
 /// Dot status enumeration
@@ -373,6 +564,61 @@ pub struct ValidationResult {
     pub warnings: Vec<String>,
 }
 
+/// Input declarations from a dot's ABI, used to validate execution requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DotInputSchema {
+    /// ABI version the inputs were read from
+    pub version: String,
+
+    /// Declared inputs, in declaration order
+    pub inputs: Vec<AbiInputField>,
+}
+
+/// A single declared ABI input
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbiInputField {
+    /// Input name
+    pub name: String,
+
+    /// Declared type
+    pub field_type: AbiFieldType,
+
+    /// Whether the input must be provided
+    pub required: bool,
+
+    /// Allowed values, if the input is an enumeration
+    pub enum_values: Vec<String>,
+}
+
+/// ABI type, e.g. `Integer` or `Array<String>`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbiFieldType {
+    /// Type name as declared in the ABI
+    pub name: String,
+
+    /// Generic parameters, such as the element type of an `Array`
+    pub params: Vec<AbiFieldType>,
+}
+
+/// A request field that failed validation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct FieldError {
+    /// Path of the offending field, e.g. `inputs.amount` or `arguments[1]`
+    pub field: String,
+
+    /// What is wrong with it
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
 // ====== General Models ======
 
 /// Health check response
@@ -452,3 +698,48 @@ pub struct DotEvent {
     /// Metadata
     pub metadata: HashMap<String, String>,
 }
+
+/// Delivers only dot events whose JSON payload has a value at a field
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, PartialEq)]
+pub struct PayloadFilter {
+    /// Dotted path into the payload, e.g. `order.status`
+    pub field: String,
+
+    /// JSON literal such as `42` or `true`; anything else is compared as a string
+    pub equals: String,
+}
+
+impl PayloadFilter {
+    /// Whether an event payload has the expected value at the filter's field
+    pub fn matches(&self, data: &serde_json::Value) -> bool {
+        let expected = serde_json::from_str(&self.equals).unwrap_or_else(|_| serde_json::Value::String(self.equals.clone()));
+        self.field.split('.').try_fold(data, |value, key| value.get(key)).is_some_and(|value| *value == expected)
+    }
+}
+
+/// VM metric for streaming
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct VmMetric {
+    /// Metric name
+    pub name: String,
+
+    /// Metric type, e.g. gauge or counter
+    #[serde(rename = "type")]
+    pub metric_type: String,
+
+    /// Samples since the previous update
+    pub data_points: Vec<MetricDataPoint>,
+
+    /// Labels
+    pub labels: HashMap<String, String>,
+}
+
+/// Single sample of a VM metric
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct MetricDataPoint {
+    /// Unix timestamp
+    pub timestamp: u64,
+
+    /// Sampled value
+    pub value: f64,
+}
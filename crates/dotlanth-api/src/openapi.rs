@@ -0,0 +1,527 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! OpenAPI document generation for the REST API
+//!
+//! Every route the router dispatches is registered in [`ROUTES`] with its method,
+//! version, path template and whether it requires a bearer token. Handler
+//! annotations supply request and response models; the registry supplies auth
+//! requirements, and every error response is documented with the RFC 7807
+//! problem details shape. The router derives its public paths from the same
+//! registry, and the tests check that each registered route is in the document.
+
+use crate::api_keys::API_KEY_HEADER;
+use crate::handlers::{admin, api_keys, auth, db, gateway, health, transactions, vm};
+use hyper::Method;
+use utoipa::openapi::path::{Operation, PathItemType};
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityRequirement, SecurityScheme};
+use utoipa::openapi::{Content, ObjectBuilder, Ref, RefOr, ResponseBuilder, Schema, SchemaType};
+use utoipa::{Modify, OpenApi};
+
+/// Path the generated document is served at
+pub const OPENAPI_JSON_PATH: &str = "/openapi.json";
+
+/// Name of the security scheme used by authenticated routes
+const BEARER_AUTH: &str = "bearer_auth";
+
+/// Name of the security scheme for API keys, accepted wherever a bearer token is
+const API_KEY_AUTH: &str = "api_key";
+
+/// Name of the error response schema
+const PROBLEM_DETAILS: &str = "ProblemDetails";
+
+/// A route served by the router
+#[derive(Debug)]
+pub struct RouteSpec {
+    pub method: Method,
+    /// First API version serving the route, e.g. 1 for `/api/v1`. Later versions
+    /// serving it are dispatched under this version's prefix.
+    pub since: u32,
+    /// Last API version serving the route, if a later version replaced or dropped it
+    pub until: Option<u32>,
+    /// Path template below the version prefix, with `{name}` for path parameters
+    pub path: &'static str,
+    /// Whether the route requires a bearer token
+    pub auth: bool,
+}
+
+impl RouteSpec {
+    const fn new(method: Method, since: u32, path: &'static str, auth: bool) -> Self {
+        Self {
+            method,
+            since,
+            until: None,
+            path,
+            auth,
+        }
+    }
+
+    /// Stop serving the route after `version`
+    pub const fn until(mut self, version: u32) -> Self {
+        self.until = Some(version);
+        self
+    }
+
+    /// Whether requests for `version` are served by this route
+    pub fn serves(&self, version: u32) -> bool {
+        self.since <= version && self.until.is_none_or(|until| version <= until)
+    }
+
+    /// Full path template including the version prefix
+    pub fn full_path(&self) -> String {
+        format!("/api/v{}{}", self.since, self.path)
+    }
+
+    /// Whether a concrete request path matches this route's template
+    pub fn matches(&self, method: &Method, path: &str) -> bool {
+        *method == self.method && template_matches(&self.full_path(), path)
+    }
+}
+
+/// Whether a concrete path matches a template with `{name}` segments
+pub(crate) fn template_matches(template: &str, path: &str) -> bool {
+    let (mut template, mut path) = (template.split('/'), path.split('/'));
+    loop {
+        match (template.next(), path.next()) {
+            (None, None) => return true,
+            (Some(t), Some(p)) if t == p || (t.starts_with('{') && t.ends_with('}') && !p.is_empty()) => {}
+            _ => return false,
+        }
+    }
+}
+
+/// Split `/api/v{n}/rest` into the version and the path below the prefix
+pub fn split_version_prefix(path: &str) -> Option<(u32, &str)> {
+    let below_api = path.strip_prefix("/api/v")?;
+    let digits = below_api.find(|c: char| !c.is_ascii_digit()).unwrap_or(below_api.len());
+    let (version, rest) = below_api.split_at(digits);
+    if version.is_empty() || !(rest.is_empty() || rest.starts_with('/')) {
+        return None;
+    }
+    Some((version.parse().ok()?, rest))
+}
+
+/// Every REST route dispatched by the router. Adding an endpoint means adding it here
+/// and annotating its handler; the tests fail if the document is missing a route.
+pub static ROUTES: &[RouteSpec] = &[
+    // Health
+    RouteSpec::new(Method::GET, 1, "/health", false),
+    RouteSpec::new(Method::GET, 1, "/ready", false),
+    RouteSpec::new(Method::GET, 1, "/version", false),
+    // Auth
+    RouteSpec::new(Method::POST, 1, "/auth/login", false),
+    RouteSpec::new(Method::GET, 1, "/auth/profile", true),
+    // Collections and documents
+    RouteSpec::new(Method::GET, 1, "/collections", true),
+    RouteSpec::new(Method::POST, 1, "/collections/{collection}", true),
+    RouteSpec::new(Method::DELETE, 1, "/collections/{collection}", true),
+    RouteSpec::new(Method::GET, 1, "/collections/{collection}/documents", true),
+    RouteSpec::new(Method::POST, 1, "/collections/{collection}/documents", true),
+    RouteSpec::new(Method::GET, 1, "/collections/{collection}/documents/{id}", true),
+    RouteSpec::new(Method::PUT, 1, "/collections/{collection}/documents/{id}", true),
+    RouteSpec::new(Method::DELETE, 1, "/collections/{collection}/documents/{id}", true),
+    RouteSpec::new(Method::GET, 1, "/collections/{collection}/search", true),
+    RouteSpec::new(Method::POST, 1, "/collections/{collection}/aggregate", true),
+    RouteSpec::new(Method::POST, 1, "/transactions", true),
+    RouteSpec::new(Method::POST, 1, "/transactions/{id}/commit", true),
+    RouteSpec::new(Method::DELETE, 1, "/transactions/{id}", true),
+    RouteSpec::new(Method::POST, 1, "/snapshots", true),
+    RouteSpec::new(Method::DELETE, 1, "/snapshots/{token}", true),
+    // VM
+    RouteSpec::new(Method::POST, 1, "/vm/dots/deploy", true),
+    RouteSpec::new(Method::GET, 1, "/vm/dots", true),
+    RouteSpec::new(Method::GET, 1, "/vm/status", true),
+    RouteSpec::new(Method::GET, 1, "/vm/architectures", true),
+    RouteSpec::new(Method::GET, 1, "/vm/dots/{id}/state", true),
+    RouteSpec::new(Method::GET, 1, "/vm/dots/{id}/state/diff", true),
+    RouteSpec::new(Method::POST, 1, "/vm/dots/{id}/execute", true),
+    RouteSpec::new(Method::DELETE, 1, "/vm/dots/{id}", true),
+    RouteSpec::new(Method::GET, 1, "/vm/dots/{id}/events", true),
+    RouteSpec::new(Method::GET, 1, "/vm/deployments/{upload_id}", true),
+    // Gateway
+    RouteSpec::new(Method::GET, 1, "/gateway/health", true),
+    RouteSpec::new(Method::GET, 1, "/gateway/metrics", true),
+    RouteSpec::new(Method::GET, 1, "/gateway/rate-limits", true),
+    RouteSpec::new(Method::GET, 1, "/gateway/cache", true),
+    RouteSpec::new(Method::GET, 1, "/gateway/admission", true),
+    RouteSpec::new(Method::GET, 1, "/gateway/config", true),
+    RouteSpec::new(Method::PUT, 1, "/gateway/config", true),
+    // Admin
+    RouteSpec::new(Method::GET, 1, "/admin/audit", true),
+    RouteSpec::new(Method::POST, 1, "/admin/compat-check", true),
+    RouteSpec::new(Method::GET, 1, "/admin/api-keys", true),
+    RouteSpec::new(Method::POST, 1, "/admin/api-keys", true),
+    RouteSpec::new(Method::POST, 1, "/admin/api-keys/{id}/rotate", true),
+    RouteSpec::new(Method::DELETE, 1, "/admin/api-keys/{id}", true),
+];
+
+/// The registered route matching a request, if any
+pub fn find_route(method: &Method, path: &str) -> Option<&'static RouteSpec> {
+    ROUTES.iter().find(|route| route.matches(method, path))
+}
+
+/// The route serving `version` for a path below the version prefix, if any
+pub fn find_versioned_route(method: &Method, version: u32, path: &str) -> Option<&'static RouteSpec> {
+    ROUTES.iter().find(|route| route.method == *method && route.serves(version) && template_matches(route.path, path))
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        // Health endpoints
+        health::health_check,
+        health::readiness_check,
+        health::version_info,
+
+        // Auth endpoints
+        auth::login,
+        auth::get_profile,
+
+        // Database endpoints
+        db::list_collections,
+        db::create_collection,
+        db::delete_collection,
+        db::get_documents,
+        db::create_document,
+        db::get_document,
+        db::update_document,
+        db::delete_document,
+        db::search_documents,
+        db::aggregate_documents,
+        db::begin_snapshot,
+        db::end_snapshot,
+        transactions::begin_transaction,
+        transactions::commit_transaction,
+        transactions::abort_transaction,
+
+        // VM endpoints
+        vm::deploy_dot,
+        vm::get_dot_state,
+        vm::get_state_diff,
+        vm::execute_dot,
+        vm::list_dots,
+        vm::delete_dot,
+        vm::get_vm_status,
+        vm::get_architectures,
+        vm::stream_dot_events,
+        vm::get_deployment_status,
+
+        // Gateway endpoints
+        gateway::gateway_health,
+        gateway::gateway_metrics,
+        gateway::rate_limit_counters,
+        gateway::response_cache_counters,
+        gateway::admission_counters,
+        gateway::get_config,
+        gateway::update_config,
+
+        // Admin endpoints
+        admin::query_audit_log,
+        admin::run_compat_check,
+        api_keys::create_api_key,
+        api_keys::list_api_keys,
+        api_keys::rotate_api_key,
+        api_keys::revoke_api_key,
+    ),
+    components(
+        schemas(
+            crate::models::TokenResponse,
+            crate::models::LoginRequest,
+            crate::models::UserProfile,
+            crate::models::Document,
+            crate::models::CreateDocumentRequest,
+            crate::models::UpdateDocumentRequest,
+            crate::models::CreateDocumentResponse,
+            crate::models::Collection,
+            crate::models::DocumentList,
+            crate::models::PaginationInfo,
+            crate::models::SearchResults,
+            crate::models::TransactionIsolation,
+            crate::models::BeginTransactionRequest,
+            crate::models::TransactionInfo,
+            crate::models::SnapshotInfo,
+            crate::models::DeployDotRequest,
+            crate::models::DeployDotResponse,
+            crate::models::DeployMetadata,
+            crate::models::DeploymentStatus,
+            crate::models::DeploymentState,
+            crate::models::DotConfig,
+            crate::models::ExecuteDotRequest,
+            crate::models::ExecuteDotResponse,
+            crate::models::DotState,
+            crate::models::StateDiff,
+            crate::models::StateChange,
+            crate::models::StateChangeKind,
+            crate::models::StateValue,
+            crate::models::ExecutionContext,
+            crate::models::DotStatus,
+            crate::models::ExecutionStatus,
+            crate::models::ValidationResult,
+            crate::models::FieldError,
+            crate::models::HealthResponse,
+            crate::models::ServiceStatus,
+            crate::models::ApiVersion,
+            crate::models::WebSocketMessage,
+            crate::models::DotEvent,
+            crate::models::PayloadFilter,
+            crate::audit::AuditRecord,
+            crate::response_cache::ResponseCacheCounters,
+            crate::admission::AdmissionConfig,
+            crate::admission::AdmissionConfigUpdate,
+            crate::admission::AdmissionCounters,
+            crate::admission::ShedCounters,
+            crate::api_keys::ApiKeyScope,
+            crate::api_keys::ApiKeyInfo,
+            crate::api_keys::CreateApiKeyRequest,
+            crate::api_keys::CreatedApiKey,
+        )
+    ),
+    tags(
+        (name = "Health", description = "Health check and version endpoints"),
+        (name = "Authentication", description = "Authentication and authorization endpoints"),
+        (name = "Database", description = "Database collection and document management"),
+        (name = "Virtual Machine", description = "VM dot deployment and execution"),
+        (name = "Gateway", description = "Gateway bridge health, metrics, rate limits and admission control"),
+        (name = "Admin", description = "Administration endpoints such as the audit log and API keys"),
+        (name = "WebSocket", description = "WebSocket streaming for real-time events")
+    ),
+    modifiers(&SecurityAddon, &ErrorShapeAddon)
+)]
+struct ApiDoc;
+
+/// Registers the bearer and API key schemes and applies them to every authenticated route in [`ROUTES`]
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(BEARER_AUTH, SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).bearer_format("JWT").build()));
+            components.add_security_scheme(API_KEY_AUTH, SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(API_KEY_HEADER))));
+        }
+
+        for route in ROUTES.iter().filter(|route| route.auth) {
+            if let Some(operation) = operation_mut(openapi, route) {
+                let security = operation.security.get_or_insert_with(Vec::new);
+                if security.is_empty() {
+                    security.push(SecurityRequirement::new(BEARER_AUTH, Vec::<String>::new()));
+                }
+                security.push(SecurityRequirement::new(API_KEY_AUTH, Vec::<String>::new()));
+                operation
+                    .responses
+                    .responses
+                    .entry("401".to_string())
+                    .or_insert_with(|| RefOr::T(ResponseBuilder::new().description("Missing or invalid bearer token or API key").build()));
+            }
+        }
+    }
+}
+
+/// Documents every 4xx/5xx response with the problem details body the error handler produces
+struct ErrorShapeAddon;
+
+impl Modify for ErrorShapeAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let string = || ObjectBuilder::new().schema_type(SchemaType::String);
+        let problem_details = ObjectBuilder::new()
+            .description(Some("RFC 7807 problem details; error-specific members may be added"))
+            .property("type", string().description(Some("URI identifying the problem type")))
+            .property("title", string().description(Some("Short summary of the problem type")))
+            .property("status", ObjectBuilder::new().schema_type(SchemaType::Integer).description(Some("HTTP status code")))
+            .property("detail", string().description(Some("Explanation of this occurrence")))
+            .property("instance", string().description(Some("Request path the problem occurred on")))
+            .required("type")
+            .required("title")
+            .required("status")
+            .required("detail")
+            .required("instance")
+            .build();
+        if let Some(components) = openapi.components.as_mut() {
+            components.schemas.insert(PROBLEM_DETAILS.to_string(), RefOr::T(Schema::Object(problem_details)));
+        }
+
+        for path_item in openapi.paths.paths.values_mut() {
+            for operation in path_item.operations.values_mut() {
+                for (status, response) in operation.responses.responses.iter_mut() {
+                    let is_error = status.starts_with('4') || status.starts_with('5');
+                    if let RefOr::T(response) = response
+                        && is_error
+                        && response.content.is_empty()
+                    {
+                        response.content.insert("application/problem+json".to_string(), Content::new(Ref::from_schema_name(PROBLEM_DETAILS)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn path_item_type(method: &Method) -> Option<PathItemType> {
+    match *method {
+        Method::GET => Some(PathItemType::Get),
+        Method::POST => Some(PathItemType::Post),
+        Method::PUT => Some(PathItemType::Put),
+        Method::DELETE => Some(PathItemType::Delete),
+        Method::PATCH => Some(PathItemType::Patch),
+        _ => None,
+    }
+}
+
+fn operation_mut<'a>(openapi: &'a mut utoipa::openapi::OpenApi, route: &RouteSpec) -> Option<&'a mut Operation> {
+    let item_type = path_item_type(&route.method)?;
+    openapi.paths.paths.get_mut(&route.full_path())?.operations.get_mut(&item_type)
+}
+
+/// Generate the OpenAPI document as pretty-printed JSON
+pub fn generate_spec() -> String {
+    ApiDoc::openapi().to_pretty_json().unwrap_or_else(|_| "{}".to_string())
+}
+
+/// Swagger UI page rendering the document served at [`OPENAPI_JSON_PATH`]
+pub fn swagger_ui_html() -> String {
+    format!(
+        r#"
+<!DOCTYPE html>
+<html>
+<head>
+    <title>Dotlanth API Documentation</title>
+    <link rel="stylesheet" type="text/css" href="https://unpkg.com/swagger-ui-dist@4.15.5/swagger-ui.css" />
+    <style>
+        html {{ box-sizing: border-box; overflow: -moz-scrollbars-vertical; overflow-y: scroll; }}
+        *, *:before, *:after {{ box-sizing: inherit; }}
+        body {{ margin:0; background: #fafafa; }}
+    </style>
+</head>
+<body>
+    <div id="swagger-ui"></div>
+    <script src="https://unpkg.com/swagger-ui-dist@4.15.5/swagger-ui-bundle.js"></script>
+    <script src="https://unpkg.com/swagger-ui-dist@4.15.5/swagger-ui-standalone-preset.js"></script>
+    <script>
+        window.onload = function() {{
+            const ui = SwaggerUIBundle({{
+                url: '{}',
+                dom_id: '#swagger-ui',
+                deepLinking: true,
+                presets: [
+                    SwaggerUIBundle.presets.apis,
+                    SwaggerUIStandalonePreset
+                ],
+                plugins: [
+                    SwaggerUIBundle.plugins.DownloadUrl
+                ],
+                layout: "StandaloneLayout"
+            }});
+        }};
+    </script>
+</body>
+</html>
+        "#,
+        OPENAPI_JSON_PATH
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn spec() -> Value {
+        serde_json::from_str(&generate_spec()).expect("generated document is valid JSON")
+    }
+
+    #[test]
+    fn test_every_registered_route_is_documented() {
+        let spec = spec();
+        assert!(spec["openapi"].as_str().is_some_and(|v| v.starts_with("3.")));
+
+        let missing: Vec<String> = ROUTES
+            .iter()
+            .filter(|route| spec["paths"][route.full_path()][route.method.as_str().to_lowercase()].is_null())
+            .map(|route| format!("{} {}", route.method, route.full_path()))
+            .collect();
+        assert!(missing.is_empty(), "routes missing from the OpenAPI document: {:?}", missing);
+    }
+
+    #[test]
+    fn test_documented_operations_are_registered() {
+        let spec = spec();
+        for (path, item) in spec["paths"].as_object().unwrap() {
+            for method in item.as_object().unwrap().keys() {
+                let method = Method::from_bytes(method.to_uppercase().as_bytes()).unwrap();
+                assert!(
+                    ROUTES.iter().any(|route| route.method == method && route.full_path() == *path),
+                    "{} {} is documented but not registered",
+                    method,
+                    path
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_auth_and_error_shapes() {
+        let spec = spec();
+        assert!(!spec["components"]["schemas"][PROBLEM_DETAILS].is_null());
+
+        for route in ROUTES {
+            let operation = &spec["paths"][route.full_path()][route.method.as_str().to_lowercase()];
+            let secured = operation["security"].as_array().is_some_and(|s| s.iter().any(|r| !r[BEARER_AUTH].is_null()));
+            assert_eq!(secured, route.auth, "security of {} {}", route.method, route.full_path());
+            if route.auth {
+                assert!(!operation["responses"]["401"].is_null(), "{} {} has no 401 response", route.method, route.full_path());
+            }
+
+            for (status, response) in operation["responses"].as_object().unwrap() {
+                if status.starts_with('4') || status.starts_with('5') {
+                    assert!(!response["content"].is_null(), "{} {} {} has no error body", route.method, route.full_path(), status);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_routes_are_under_version_prefix() {
+        for route in ROUTES {
+            assert!(route.full_path().starts_with(&format!("/api/v{}/", route.since)));
+        }
+        let login = find_route(&Method::POST, "/api/v1/auth/login").unwrap();
+        assert!(!login.auth);
+    }
+
+    #[test]
+    fn test_route_matching() {
+        let route = find_route(&Method::PUT, "/api/v1/collections/users/documents/42").unwrap();
+        assert_eq!(route.path, "/collections/{collection}/documents/{id}");
+        assert!(find_route(&Method::GET, "/api/v1/collections/users/documents/42/extra").is_none());
+        assert!(find_route(&Method::GET, "/api/v1/collections//documents").is_none());
+        assert!(find_route(&Method::PATCH, "/api/v1/collections").is_none());
+        assert_eq!(find_route(&Method::GET, "/api/v1/vm/dots/deploy").map(|r| r.path), None);
+    }
+
+    #[test]
+    fn test_versioned_route_matching() {
+        assert_eq!(split_version_prefix("/api/v12/vm/dots"), Some((12, "/vm/dots")));
+        assert_eq!(split_version_prefix("/api/v2"), Some((2, "")));
+        assert_eq!(split_version_prefix("/api/vm/dots"), None);
+        assert_eq!(split_version_prefix("/api/v2x/dots"), None);
+
+        let route = find_versioned_route(&Method::GET, 3, "/vm/dots/abc/state").unwrap();
+        assert_eq!(route.full_path(), "/api/v1/vm/dots/{id}/state");
+        assert!(find_versioned_route(&Method::GET, 0, "/vm/dots/abc/state").is_none());
+
+        let retired = RouteSpec::new(Method::GET, 1, "/legacy", true).until(2);
+        assert!(retired.serves(1) && retired.serves(2) && !retired.serves(3));
+    }
+}
@@ -19,15 +19,28 @@
 //! - Token Bucket
 //! - Sliding Window
 //! - Fixed Window Counter
+//!
+//! The [`PriorityRateLimiter`] applies per-API-key token buckets on top of a shared
+//! gateway bucket, throttling `batch` tier keys before `interactive` ones.
 
 use crate::error::{ApiError, ApiResult};
+use async_trait::async_trait;
 use dashmap::DashMap;
+use hyper::Response;
+use hyper::header::{HeaderName, HeaderValue, RETRY_AFTER};
+use metrics::counter;
+use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::Mutex;
-use tracing::{debug, warn};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
 
 /// Rate limit configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -382,6 +395,597 @@ impl RateLimiterManager {
     }
 }
 
+/// Priority tier of an API key
+///
+/// Interactive traffic may draw the shared gateway bucket down to zero, while batch
+/// traffic is throttled once it reaches the reserve kept for interactive keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitTier {
+    /// Latency sensitive clients, never throttled by gateway-wide pressure
+    #[default]
+    Interactive,
+
+    /// Background integrations, throttled first under load
+    Batch,
+}
+
+impl RateLimitTier {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RateLimitTier::Interactive => "interactive",
+            RateLimitTier::Batch => "batch",
+        }
+    }
+}
+
+impl FromStr for RateLimitTier {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "interactive" => Ok(RateLimitTier::Interactive),
+            "batch" => Ok(RateLimitTier::Batch),
+            other => Err(format!("Unknown rate limit tier: {}", other)),
+        }
+    }
+}
+
+/// Token bucket parameters
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BucketLimits {
+    /// Tokens added per second
+    pub rate_per_second: f64,
+
+    /// Bucket capacity, i.e. the largest burst allowed
+    pub burst: u32,
+}
+
+impl BucketLimits {
+    pub fn new(rate_per_second: f64, burst: u32) -> Self {
+        Self { rate_per_second, burst }
+    }
+}
+
+impl FromStr for BucketLimits {
+    type Err = String;
+
+    /// Parse `RATE:BURST`, e.g. `50:100`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (rate, burst) = s.split_once(':').ok_or_else(|| format!("Expected RATE:BURST, got {}", s))?;
+        let rate_per_second = rate.trim().parse::<f64>().map_err(|e| format!("Invalid rate {}: {}", rate, e))?;
+        let burst = burst.trim().parse::<u32>().map_err(|e| format!("Invalid burst {}: {}", burst, e))?;
+        if rate_per_second <= 0.0 || burst == 0 {
+            return Err(format!("Rate and burst must be positive, got {}", s));
+        }
+        Ok(Self { rate_per_second, burst })
+    }
+}
+
+/// Limits configured for a single API key
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiKeyLimit {
+    /// Priority tier of the key
+    pub tier: RateLimitTier,
+
+    /// Bucket for this key, defaults to the tier's limits
+    #[serde(default)]
+    pub limits: Option<BucketLimits>,
+}
+
+/// Configuration of the [`PriorityRateLimiter`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PriorityRateLimitConfig {
+    /// Whether rate limiting is applied at all
+    pub enabled: bool,
+
+    /// Per-key bucket for interactive keys without an explicit limit
+    pub interactive: BucketLimits,
+
+    /// Per-key bucket for batch keys without an explicit limit
+    pub batch: BucketLimits,
+
+    /// Per-client bucket for requests without an API key
+    pub anonymous: BucketLimits,
+
+    /// Bucket shared by all requests passing through the gateway
+    pub gateway: BucketLimits,
+
+    /// Fraction of the gateway burst held back for interactive traffic
+    pub batch_reserve: f64,
+
+    /// Tier of authenticated API keys that are not listed in `keys`
+    pub default_tier: RateLimitTier,
+
+    /// Per-key overrides, keyed by [`api_key_fingerprint`]
+    pub keys: HashMap<String, ApiKeyLimit>,
+}
+
+impl Default for PriorityRateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interactive: BucketLimits::new(20.0, 40),
+            batch: BucketLimits::new(10.0, 20),
+            anonymous: BucketLimits::new(2.0, 10),
+            gateway: BucketLimits::new(500.0, 1000),
+            batch_reserve: 0.2,
+            default_tier: RateLimitTier::Interactive,
+            keys: HashMap::new(),
+        }
+    }
+}
+
+impl PriorityRateLimitConfig {
+    /// Load the configuration from `DOTLANTH_RATE_LIMIT_*` environment variables
+    ///
+    /// Bucket variables take `RATE:BURST`. `DOTLANTH_RATE_LIMIT_KEYS` lists
+    /// per-key entries as `FINGERPRINT=TIER[:RATE:BURST]` separated by `;`.
+    /// Malformed values are logged and the default is kept.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Ok(v) = env::var("DOTLANTH_RATE_LIMIT_ENABLED") {
+            config.enabled = v.parse().unwrap_or(config.enabled);
+        }
+        for (name, limits) in [
+            ("DOTLANTH_RATE_LIMIT_INTERACTIVE", &mut config.interactive),
+            ("DOTLANTH_RATE_LIMIT_BATCH", &mut config.batch),
+            ("DOTLANTH_RATE_LIMIT_ANONYMOUS", &mut config.anonymous),
+            ("DOTLANTH_RATE_LIMIT_GATEWAY", &mut config.gateway),
+        ] {
+            if let Ok(v) = env::var(name) {
+                match v.parse() {
+                    Ok(parsed) => *limits = parsed,
+                    Err(e) => warn!("Ignoring {}: {}", name, e),
+                }
+            }
+        }
+        if let Ok(v) = env::var("DOTLANTH_RATE_LIMIT_BATCH_RESERVE") {
+            match v.parse::<f64>() {
+                Ok(reserve) if (0.0..=1.0).contains(&reserve) => config.batch_reserve = reserve,
+                _ => warn!("Ignoring DOTLANTH_RATE_LIMIT_BATCH_RESERVE: expected a fraction between 0 and 1"),
+            }
+        }
+        if let Ok(v) = env::var("DOTLANTH_RATE_LIMIT_DEFAULT_TIER") {
+            match v.parse() {
+                Ok(tier) => config.default_tier = tier,
+                Err(e) => warn!("Ignoring DOTLANTH_RATE_LIMIT_DEFAULT_TIER: {}", e),
+            }
+        }
+        if let Ok(v) = env::var("DOTLANTH_RATE_LIMIT_KEYS") {
+            for entry in v.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+                match parse_key_entry(entry) {
+                    Ok((fingerprint, limit)) => {
+                        config.keys.insert(fingerprint, limit);
+                    }
+                    Err(e) => warn!("Ignoring rate limit key entry: {}", e),
+                }
+            }
+        }
+
+        config
+    }
+
+    /// Load the configuration from a JSON file
+    pub fn from_file(path: &Path) -> ApiResult<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    /// Limits and tier that apply to an API key
+    fn key_limits(&self, fingerprint: &str) -> (RateLimitTier, BucketLimits) {
+        match self.keys.get(fingerprint) {
+            Some(entry) => (entry.tier, entry.limits.unwrap_or_else(|| self.tier_limits(entry.tier))),
+            None => (self.default_tier, self.tier_limits(self.default_tier)),
+        }
+    }
+
+    fn tier_limits(&self, tier: RateLimitTier) -> BucketLimits {
+        match tier {
+            RateLimitTier::Interactive => self.interactive,
+            RateLimitTier::Batch => self.batch,
+        }
+    }
+}
+
+/// Parse a `FINGERPRINT=TIER[:RATE:BURST]` entry
+fn parse_key_entry(entry: &str) -> Result<(String, ApiKeyLimit), String> {
+    let (fingerprint, spec) = entry.split_once('=').ok_or_else(|| format!("Expected FINGERPRINT=TIER, got {}", entry))?;
+    let (tier, limits) = match spec.split_once(':') {
+        Some((tier, limits)) => (tier, Some(limits.parse()?)),
+        None => (spec, None),
+    };
+    Ok((fingerprint.trim().to_string(), ApiKeyLimit { tier: tier.parse()?, limits }))
+}
+
+/// Stable identifier of an API key used in configuration and counters
+///
+/// The first 8 bytes of the key's SHA-256 digest, hex encoded, so raw keys never
+/// appear in config files or counters.
+pub fn api_key_fingerprint(api_key: &str) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, api_key.as_bytes());
+    digest.as_ref()[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// State of a bucket after an operation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BucketState {
+    /// Whether the tokens were taken
+    pub allowed: bool,
+
+    /// Tokens left in the bucket
+    pub remaining: f64,
+
+    /// Time until the request would be allowed
+    pub retry_after: Duration,
+}
+
+/// Storage backend of token buckets
+///
+/// The in-memory implementation keeps buckets per process; a shared backend such as
+/// Redis can implement the same operations to limit across gateway replicas.
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Take `cost` tokens if at least `floor + cost` are available after refilling
+    async fn acquire(&self, key: &str, limits: BucketLimits, cost: f64, floor: f64) -> BucketState;
+
+    /// Take up to `cost` tokens unconditionally, never going below zero
+    async fn consume(&self, key: &str, limits: BucketLimits, cost: f64) -> BucketState;
+
+    /// Return tokens taken by a request that was rejected further along
+    async fn release(&self, key: &str, limits: BucketLimits, cost: f64);
+}
+
+/// Process-local [`RateLimitStore`]
+///
+/// A bucket that has refilled to its burst is the same as no bucket, so such buckets
+/// are dropped whenever the map has doubled since the last sweep. Memory then follows
+/// the number of recently active callers rather than every caller ever seen.
+#[derive(Debug)]
+pub struct InMemoryRateLimitStore {
+    /// Tokens, time of the last refill and the time the bucket is full again
+    buckets: DashMap<String, (f64, Instant, Instant)>,
+    sweep_at: AtomicUsize,
+}
+
+impl Default for InMemoryRateLimitStore {
+    fn default() -> Self {
+        Self {
+            buckets: DashMap::new(),
+            sweep_at: AtomicUsize::new(MIN_SWEEP_BUCKETS),
+        }
+    }
+}
+
+/// Number of buckets below which full buckets are never swept
+const MIN_SWEEP_BUCKETS: usize = 1024;
+
+impl InMemoryRateLimitStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Refill the bucket and apply `update` to its token count
+    fn update<T>(&self, key: &str, limits: BucketLimits, update: impl FnOnce(&mut f64) -> T) -> T {
+        let now = Instant::now();
+        let capacity = limits.burst as f64;
+        let result = {
+            let mut entry = self.buckets.entry(key.to_string()).or_insert((capacity, now, now));
+            let (tokens, last_refill, full_at) = entry.value_mut();
+
+            let refilled = *tokens + now.duration_since(*last_refill).as_secs_f64() * limits.rate_per_second;
+            *tokens = refilled.min(capacity);
+            *last_refill = now;
+
+            let result = update(tokens);
+            let refill_secs = if *tokens >= capacity {
+                0.0
+            } else {
+                ((capacity - *tokens) / limits.rate_per_second).min(u32::MAX as f64)
+            };
+            *full_at = now + Duration::from_secs_f64(refill_secs);
+            result
+        };
+
+        if self.buckets.len() >= self.sweep_at.load(Ordering::Relaxed) {
+            self.sweep(now);
+        }
+        result
+    }
+
+    /// Drop the buckets that are full by `now`
+    fn sweep(&self, now: Instant) {
+        self.buckets.retain(|_, (_, _, full_at)| *full_at > now);
+        self.sweep_at.store((self.buckets.len() * 2).max(MIN_SWEEP_BUCKETS), Ordering::Relaxed);
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for InMemoryRateLimitStore {
+    async fn acquire(&self, key: &str, limits: BucketLimits, cost: f64, floor: f64) -> BucketState {
+        self.update(key, limits, |tokens| {
+            if *tokens - cost >= floor {
+                *tokens -= cost;
+                BucketState {
+                    allowed: true,
+                    remaining: *tokens,
+                    retry_after: Duration::ZERO,
+                }
+            } else {
+                let missing = floor + cost - *tokens;
+                BucketState {
+                    allowed: false,
+                    remaining: *tokens,
+                    retry_after: Duration::from_secs_f64(missing / limits.rate_per_second),
+                }
+            }
+        })
+    }
+
+    async fn consume(&self, key: &str, limits: BucketLimits, cost: f64) -> BucketState {
+        self.update(key, limits, |tokens| {
+            *tokens = (*tokens - cost).max(0.0);
+            BucketState {
+                allowed: true,
+                remaining: *tokens,
+                retry_after: Duration::ZERO,
+            }
+        })
+    }
+
+    async fn release(&self, key: &str, limits: BucketLimits, cost: f64) {
+        self.update(key, limits, |tokens| *tokens = (*tokens + cost).min(limits.burst as f64));
+    }
+}
+
+/// Outcome of a rate limit check
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitDecision {
+    /// Whether the request may proceed
+    pub allowed: bool,
+
+    /// Tier the request was classified as
+    pub tier: RateLimitTier,
+
+    /// Burst size of the caller's bucket
+    pub limit: u32,
+
+    /// Whole tokens left in the caller's bucket
+    pub remaining: u32,
+
+    /// Time until the caller may retry, zero when allowed
+    pub retry_after: Duration,
+}
+
+impl RateLimitDecision {
+    fn unlimited() -> Self {
+        Self {
+            allowed: true,
+            tier: RateLimitTier::Interactive,
+            limit: u32::MAX,
+            remaining: u32::MAX,
+            retry_after: Duration::ZERO,
+        }
+    }
+
+    /// Error returned to throttled callers
+    pub fn to_error(&self) -> ApiError {
+        ApiError::TooManyRequests {
+            message: format!("Rate limit exceeded. Try again in {} seconds", self.retry_after_secs()),
+        }
+    }
+
+    /// `Retry-After` value, rounded up to whole seconds
+    pub fn retry_after_secs(&self) -> u64 {
+        self.retry_after.as_secs_f64().ceil().max(1.0) as u64
+    }
+
+    /// Add `X-RateLimit-*` headers, and `Retry-After` when throttled
+    pub fn apply_headers<B>(&self, response: &mut Response<B>) {
+        if self.limit == u32::MAX {
+            return;
+        }
+
+        let headers = response.headers_mut();
+        headers.insert(HeaderName::from_static("x-ratelimit-limit"), HeaderValue::from(self.limit));
+        headers.insert(HeaderName::from_static("x-ratelimit-remaining"), HeaderValue::from(self.remaining));
+        if !self.allowed {
+            headers.insert(RETRY_AFTER, HeaderValue::from(self.retry_after_secs()));
+        }
+    }
+}
+
+/// Request counters of a single caller
+#[derive(Debug, Default)]
+struct CallerCounters {
+    allowed: AtomicU64,
+    throttled: AtomicU64,
+}
+
+/// Snapshot of a caller's request counters
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitCounters {
+    /// API key fingerprint, or `anonymous` for requests without a key
+    pub key: String,
+
+    /// Tier of the caller
+    pub tier: RateLimitTier,
+
+    /// Requests allowed
+    pub allowed: u64,
+
+    /// Requests throttled
+    pub throttled: u64,
+}
+
+/// Name of the shared gateway bucket
+const GATEWAY_BUCKET: &str = "gateway";
+
+/// Counter label used for requests without an API key
+const ANONYMOUS_KEY: &str = "anonymous";
+
+/// Per-API-key, priority-aware rate limiter
+///
+/// Every request takes a token from its caller's bucket and from the shared gateway
+/// bucket. Interactive keys only fail on their own bucket; batch keys and anonymous
+/// clients also fail once the gateway bucket drops to its interactive reserve.
+///
+/// Only API keys listed in the configuration or authenticated by the caller get a
+/// bucket of their own. Any other key counts as anonymous traffic from its client, so
+/// rotating made-up keys neither escapes the limits nor grows the limiter's state.
+pub struct PriorityRateLimiter {
+    config: RwLock<Arc<PriorityRateLimitConfig>>,
+    store: Arc<dyn RateLimitStore>,
+    counters: DashMap<(String, RateLimitTier), CallerCounters>,
+}
+
+impl PriorityRateLimiter {
+    /// Create a limiter backed by an [`InMemoryRateLimitStore`]
+    pub fn new(config: PriorityRateLimitConfig) -> Self {
+        Self::with_store(config, Arc::new(InMemoryRateLimitStore::new()))
+    }
+
+    /// Create a limiter backed by a custom store
+    pub fn with_store(config: PriorityRateLimitConfig, store: Arc<dyn RateLimitStore>) -> Self {
+        Self {
+            config: RwLock::new(Arc::new(config)),
+            store,
+            counters: DashMap::new(),
+        }
+    }
+
+    /// Current configuration
+    pub fn config(&self) -> Arc<PriorityRateLimitConfig> {
+        self.config.read().clone()
+    }
+
+    /// Replace the configuration; existing buckets keep their tokens, capped at the new burst
+    pub fn reload(&self, config: PriorityRateLimitConfig) {
+        *self.config.write() = Arc::new(config);
+        info!("Rate limit configuration reloaded");
+    }
+
+    /// Check a request made with `api_key`, or by `client` when no key is given
+    ///
+    /// `authenticated` tells whether `api_key` was verified against the key store.
+    pub async fn check(&self, api_key: Option<&str>, authenticated: bool, client: &str) -> RateLimitDecision {
+        let config = self.config();
+        if !config.enabled {
+            return RateLimitDecision::unlimited();
+        }
+
+        let (counter_key, bucket_key, tier, limits) = match api_key.map(api_key_fingerprint) {
+            Some(fingerprint) if authenticated || config.keys.contains_key(&fingerprint) => {
+                let (tier, limits) = config.key_limits(&fingerprint);
+                (fingerprint.clone(), format!("api_key:{}", fingerprint), tier, limits)
+            }
+            // Anonymous traffic has no priority over batch integrations
+            _ => (ANONYMOUS_KEY.to_string(), format!("client:{}", client), RateLimitTier::Batch, config.anonymous),
+        };
+
+        let own = self.store.acquire(&bucket_key, limits, 1.0, 0.0).await;
+        let retry_after = if !own.allowed {
+            Some(own.retry_after)
+        } else {
+            match tier {
+                RateLimitTier::Interactive => {
+                    self.store.consume(GATEWAY_BUCKET, config.gateway, 1.0).await;
+                    None
+                }
+                RateLimitTier::Batch => {
+                    let reserve = config.gateway.burst as f64 * config.batch_reserve;
+                    let gateway = self.store.acquire(GATEWAY_BUCKET, config.gateway, 1.0, reserve).await;
+                    if gateway.allowed {
+                        None
+                    } else {
+                        self.store.release(&bucket_key, limits, 1.0).await;
+                        Some(gateway.retry_after)
+                    }
+                }
+            }
+        };
+
+        let counters = self.counters.entry((counter_key.clone(), tier)).or_default();
+        match retry_after {
+            None => {
+                counters.allowed.fetch_add(1, Ordering::Relaxed);
+                counter!("rate_limit_allowed_total", 1, "tier" => tier.as_str());
+                RateLimitDecision {
+                    allowed: true,
+                    tier,
+                    limit: limits.burst,
+                    remaining: own.remaining as u32,
+                    retry_after: Duration::ZERO,
+                }
+            }
+            Some(retry_after) => {
+                counters.throttled.fetch_add(1, Ordering::Relaxed);
+                counter!("rate_limit_throttled_total", 1, "tier" => tier.as_str());
+                debug!("Throttled {} ({} tier) for {:?}", counter_key, tier.as_str(), retry_after);
+                RateLimitDecision {
+                    allowed: false,
+                    tier,
+                    limit: limits.burst,
+                    remaining: 0,
+                    retry_after,
+                }
+            }
+        }
+    }
+
+    /// Allowed and throttled counts per caller
+    pub fn counters(&self) -> Vec<RateLimitCounters> {
+        let mut counters: Vec<_> = self
+            .counters
+            .iter()
+            .map(|entry| {
+                let (key, tier) = entry.key();
+                RateLimitCounters {
+                    key: key.clone(),
+                    tier: *tier,
+                    allowed: entry.value().allowed.load(Ordering::Relaxed),
+                    throttled: entry.value().throttled.load(Ordering::Relaxed),
+                }
+            })
+            .collect();
+        counters.sort_by(|a, b| a.key.cmp(&b.key));
+        counters
+    }
+
+    /// Poll `path` and reload the configuration whenever the file changes
+    ///
+    /// Invalid files are logged and ignored, keeping the previous configuration.
+    pub fn watch_config_file(self: &Arc<Self>, path: PathBuf, interval: Duration) -> JoinHandle<()> {
+        let limiter = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut last_modified = None;
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+
+                let modified = match tokio::fs::metadata(&path).await.and_then(|m| m.modified()) {
+                    Ok(modified) => modified,
+                    Err(e) => {
+                        debug!("Rate limit config {} unavailable: {}", path.display(), e);
+                        continue;
+                    }
+                };
+                if last_modified == Some(modified) {
+                    continue;
+                }
+                last_modified = Some(modified);
+
+                match PriorityRateLimitConfig::from_file(&path) {
+                    Ok(config) => limiter.reload(config),
+                    Err(e) => warn!("Ignoring invalid rate limit config {}: {}", path.display(), e),
+                }
+            }
+        })
+    }
+}
+
 /// Serde helper for Duration serialization
 mod serde_duration {
     use serde::{Deserialize, Deserializer, Serializer};
@@ -408,6 +1012,22 @@ mod tests {
     use super::*;
     use std::thread;
 
+    #[tokio::test]
+    async fn test_full_buckets_are_swept() {
+        let store = InMemoryRateLimitStore::new();
+        let limits = BucketLimits::new(1000.0, 1);
+
+        for client in 0..MIN_SWEEP_BUCKETS - 1 {
+            store.acquire(&format!("client:{}", client), limits, 1.0, 0.0).await;
+        }
+        assert_eq!(store.buckets.len(), MIN_SWEEP_BUCKETS - 1);
+
+        // Every bucket refills within a few milliseconds, so the next new one sweeps them
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        store.acquire("client:new", BucketLimits::new(0.001, 1), 1.0, 0.0).await;
+        assert_eq!(store.buckets.len(), 1);
+    }
+
     #[test]
     fn test_token_bucket() {
         let bucket = TokenBucket::new(10, Duration::from_secs(1));
@@ -455,4 +1075,133 @@ mod tests {
         let info = counter.is_allowed(key);
         assert!(!info.allowed);
     }
+
+    fn priority_config() -> PriorityRateLimitConfig {
+        let mut config = PriorityRateLimitConfig {
+            interactive: BucketLimits::new(0.001, 5),
+            batch: BucketLimits::new(0.001, 5),
+            anonymous: BucketLimits::new(0.001, 2),
+            gateway: BucketLimits::new(0.001, 10),
+            batch_reserve: 0.5,
+            ..PriorityRateLimitConfig::default()
+        };
+        config.keys.insert(
+            api_key_fingerprint("batch-key"),
+            ApiKeyLimit {
+                tier: RateLimitTier::Batch,
+                limits: None,
+            },
+        );
+        config
+    }
+
+    #[tokio::test]
+    async fn test_per_key_buckets_are_independent() {
+        let limiter = PriorityRateLimiter::new(priority_config());
+
+        for _ in 0..5 {
+            assert!(limiter.check(Some("key-a"), true, "127.0.0.1").await.allowed);
+        }
+        let throttled = limiter.check(Some("key-a"), true, "127.0.0.1").await;
+        assert!(!throttled.allowed);
+        assert_eq!(throttled.remaining, 0);
+        assert!(throttled.retry_after_secs() >= 1);
+
+        assert!(limiter.check(Some("key-b"), true, "127.0.0.1").await.allowed);
+    }
+
+    #[tokio::test]
+    async fn test_batch_throttled_before_interactive() {
+        let limiter = PriorityRateLimiter::new(priority_config());
+
+        // Interactive traffic drains the gateway bucket down to its reserve
+        for key in ["key-a", "key-b"] {
+            for _ in 0..3 {
+                assert!(limiter.check(Some(key), true, "127.0.0.1").await.allowed);
+            }
+        }
+
+        let batch = limiter.check(Some("batch-key"), false, "127.0.0.1").await;
+        assert_eq!(batch.tier, RateLimitTier::Batch);
+        assert!(!batch.allowed);
+
+        // Interactive keys still get through while batch is throttled
+        let interactive = limiter.check(Some("key-c"), true, "127.0.0.1").await;
+        assert_eq!(interactive.tier, RateLimitTier::Interactive);
+        assert!(interactive.allowed);
+    }
+
+    #[tokio::test]
+    async fn test_counters_and_reload() {
+        let limiter = PriorityRateLimiter::new(priority_config());
+
+        for _ in 0..3 {
+            limiter.check(None, false, "10.0.0.1").await;
+        }
+        let counters = limiter.counters();
+        assert_eq!(counters.len(), 1);
+        assert_eq!(counters[0].key, "anonymous");
+        assert_eq!(counters[0].allowed, 2);
+        assert_eq!(counters[0].throttled, 1);
+
+        limiter.reload(PriorityRateLimitConfig {
+            enabled: false,
+            ..priority_config()
+        });
+        assert!(limiter.check(None, false, "10.0.0.1").await.allowed);
+    }
+
+    #[tokio::test]
+    async fn test_unverified_keys_are_limited_as_anonymous() {
+        let limiter = PriorityRateLimiter::new(priority_config());
+
+        // Made-up keys share their client's anonymous bucket instead of getting fresh ones
+        for key in ["made-up-1", "made-up-2"] {
+            let decision = limiter.check(Some(key), false, "10.0.0.2").await;
+            assert!(decision.allowed);
+            assert_eq!(decision.tier, RateLimitTier::Batch);
+        }
+        assert!(!limiter.check(Some("made-up-3"), false, "10.0.0.2").await.allowed);
+        assert!(limiter.check(Some("made-up-4"), false, "10.0.0.3").await.allowed);
+
+        // Listed keys need no authentication; their fingerprint proves the key is known
+        assert!(limiter.check(Some("batch-key"), false, "10.0.0.2").await.allowed);
+
+        let counters = limiter.counters();
+        assert_eq!(counters.len(), 2);
+        assert!(counters.iter().any(|c| c.key == api_key_fingerprint("batch-key")));
+        let anonymous = counters.iter().find(|c| c.key == "anonymous").unwrap();
+        assert_eq!((anonymous.allowed, anonymous.throttled), (3, 1));
+    }
+
+    #[test]
+    fn test_throttled_headers() {
+        let decision = RateLimitDecision {
+            allowed: false,
+            tier: RateLimitTier::Batch,
+            limit: 20,
+            remaining: 0,
+            retry_after: Duration::from_millis(1500),
+        };
+        let mut response = Response::new(());
+        decision.apply_headers(&mut response);
+
+        assert_eq!(response.headers()["retry-after"], "2");
+        assert_eq!(response.headers()["x-ratelimit-remaining"], "0");
+        assert_eq!(response.headers()["x-ratelimit-limit"], "20");
+    }
+
+    #[test]
+    fn test_parse_key_entry() {
+        let (fingerprint, limit) = parse_key_entry("abc123=batch:5:10").unwrap();
+        assert_eq!(fingerprint, "abc123");
+        assert_eq!(limit.tier, RateLimitTier::Batch);
+        assert_eq!(limit.limits, Some(BucketLimits::new(5.0, 10)));
+
+        let (_, limit) = parse_key_entry("def456=interactive").unwrap();
+        assert_eq!(limit.limits, None);
+
+        assert!(parse_key_entry("def456=realtime").is_err());
+        assert!(parse_key_entry("def456").is_err());
+    }
 }
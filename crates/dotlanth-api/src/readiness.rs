@@ -0,0 +1,531 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Readiness of the gateway's dependencies
+//!
+//! `/readyz` probes every dependency at once, each within the probe timeout. A
+//! hard dependency that is down makes the gateway not ready (503); a soft one
+//! only adds a warning to an otherwise ready response. Which dependencies are
+//! hard is configurable. A report is reused for `cache_ttl_ms`, and concurrent
+//! requests wait for the round of probes already running rather than starting
+//! their own, so load balancer checks do not hammer the dependencies. Every
+//! change of a dependency's status, and of the overall status, is logged.
+
+use crate::auth::AuthService;
+use crate::db::pipeline::RuntimeStateBackend;
+use crate::grpc_pool::parse_env;
+use crate::vm::VmClient;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+/// Whether a dependency being down makes the gateway not ready
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyClass {
+    /// Requests cannot be served without it
+    Hard,
+    /// Requests are served without it, with a warning
+    Soft,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyStatus {
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadinessStatus {
+    /// Every dependency is up
+    Ready,
+    /// Every hard dependency is up, some soft one is down
+    Degraded,
+    /// Some hard dependency is down
+    NotReady,
+    /// Shutdown has started; dependencies are not probed
+    ShuttingDown,
+}
+
+impl ReadinessStatus {
+    /// Whether load balancers should keep routing traffic here
+    pub fn is_ready(&self) -> bool {
+        matches!(self, Self::Ready | Self::Degraded)
+    }
+}
+
+impl fmt::Display for ReadinessStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ready => "ready",
+            Self::Degraded => "degraded",
+            Self::NotReady => "not_ready",
+            Self::ShuttingDown => "shutting_down",
+        })
+    }
+}
+
+/// Readiness probe settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReadinessConfig {
+    /// Time each dependency gets to answer before it counts as down
+    pub probe_timeout_ms: u64,
+    /// How long a report is served before the dependencies are probed again
+    pub cache_ttl_ms: u64,
+    /// Dependencies classified differently from their default, by name
+    pub classes: HashMap<String, DependencyClass>,
+}
+
+impl Default for ReadinessConfig {
+    fn default() -> Self {
+        Self {
+            probe_timeout_ms: 2_000,
+            cache_ttl_ms: 1_000,
+            classes: HashMap::new(),
+        }
+    }
+}
+
+impl ReadinessConfig {
+    /// Load readiness settings from `DOTLANTH_READINESS_*` variables
+    ///
+    /// `DOTLANTH_READINESS_HARD` and `DOTLANTH_READINESS_SOFT` list dependency names,
+    /// comma-separated, to classify as hard or soft.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        let list = |name: &str| parse_env::<String>(name).map(|value| value.split(',').map(str::trim).filter(|item| !item.is_empty()).map(str::to_string).collect::<Vec<_>>());
+
+        if let Some(probe_timeout_ms) = parse_env::<u64>("DOTLANTH_READINESS_PROBE_TIMEOUT_MS").filter(|&ms| ms > 0) {
+            config.probe_timeout_ms = probe_timeout_ms;
+        }
+        if let Some(cache_ttl_ms) = parse_env("DOTLANTH_READINESS_CACHE_TTL_MS") {
+            config.cache_ttl_ms = cache_ttl_ms;
+        }
+        for (variable, class) in [("DOTLANTH_READINESS_HARD", DependencyClass::Hard), ("DOTLANTH_READINESS_SOFT", DependencyClass::Soft)] {
+            for name in list(variable).unwrap_or_default() {
+                config.classes.insert(name, class);
+            }
+        }
+
+        config
+    }
+
+    /// Classify `name` as `class`
+    pub fn with_class(mut self, name: impl Into<String>, class: DependencyClass) -> Self {
+        self.classes.insert(name.into(), class);
+        self
+    }
+}
+
+/// A dependency `/readyz` checks
+#[async_trait]
+pub trait DependencyProbe: Send + Sync {
+    /// Name the dependency is reported and configured under
+    fn name(&self) -> &str;
+
+    /// Class used unless the configuration says otherwise
+    fn default_class(&self) -> DependencyClass;
+
+    /// Check the dependency, describing why it is down on failure
+    async fn check(&self) -> Result<(), String>;
+}
+
+/// Status of one dependency
+#[derive(Debug, Clone, Serialize)]
+pub struct DependencyReport {
+    pub name: String,
+    pub class: DependencyClass,
+    pub status: DependencyStatus,
+    pub latency_ms: u64,
+    /// Why the dependency is down
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Body of `/readyz`
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadinessReport {
+    pub status: ReadinessStatus,
+    pub checked_at: DateTime<Utc>,
+    pub dependencies: Vec<DependencyReport>,
+    /// Soft dependencies that are down
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+impl ReadinessReport {
+    /// Report for a gateway that is shutting down
+    pub fn shutting_down() -> Self {
+        Self {
+            status: ReadinessStatus::ShuttingDown,
+            checked_at: Utc::now(),
+            dependencies: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+struct CachedReport {
+    report: ReadinessReport,
+    expires_at: Instant,
+}
+
+/// Probes the gateway's dependencies for `/readyz`
+pub struct ReadinessChecker {
+    config: ReadinessConfig,
+    probes: Vec<Arc<dyn DependencyProbe>>,
+    /// Latest report, locked while probing so concurrent checks share one round
+    cached: Mutex<Option<CachedReport>>,
+}
+
+impl ReadinessChecker {
+    pub fn new(config: ReadinessConfig) -> Self {
+        Self {
+            config,
+            probes: Vec::new(),
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Checker probing the runtime, its database service, token validation and the metrics recorder
+    pub fn for_gateway(config: ReadinessConfig, vm_client: &VmClient, auth_service: Arc<Mutex<AuthService>>) -> Self {
+        Self::new(config)
+            .with_probe(RuntimeProbe { vm_client: vm_client.clone() })
+            .with_probe(DotDbProbe { backend: vm_client.state_backend() })
+            .with_probe(AuthProbe { auth_service })
+            .with_probe(MetricsProbe)
+    }
+
+    /// Also check `probe`
+    pub fn with_probe(mut self, probe: impl DependencyProbe + 'static) -> Self {
+        self.probes.push(Arc::new(probe));
+        self
+    }
+
+    pub fn config(&self) -> &ReadinessConfig {
+        &self.config
+    }
+
+    fn class_of(&self, probe: &dyn DependencyProbe) -> DependencyClass {
+        self.config.classes.get(probe.name()).copied().unwrap_or_else(|| probe.default_class())
+    }
+
+    /// The latest report, probing the dependencies again once it is older than the cache TTL
+    pub async fn check(&self) -> ReadinessReport {
+        let mut cached = self.cached.lock().await;
+        if let Some(entry) = cached.as_ref()
+            && Instant::now() < entry.expires_at
+        {
+            return entry.report.clone();
+        }
+
+        let report = self.probe_all().await;
+        log_transitions(cached.as_ref().map(|entry| &entry.report), &report);
+        *cached = Some(CachedReport {
+            report: report.clone(),
+            expires_at: Instant::now() + Duration::from_millis(self.config.cache_ttl_ms),
+        });
+        report
+    }
+
+    async fn probe_all(&self) -> ReadinessReport {
+        let timeout = Duration::from_millis(self.config.probe_timeout_ms);
+        let dependencies = join_all(self.probes.iter().map(|probe| async move {
+            let started = Instant::now();
+            let result = match tokio::time::timeout(timeout, probe.check()).await {
+                Ok(result) => result,
+                Err(_) => Err(format!("no answer within {}ms", timeout.as_millis())),
+            };
+            DependencyReport {
+                name: probe.name().to_string(),
+                class: self.class_of(probe.as_ref()),
+                status: if result.is_ok() { DependencyStatus::Up } else { DependencyStatus::Down },
+                latency_ms: started.elapsed().as_millis() as u64,
+                error: result.err(),
+            }
+        }))
+        .await;
+
+        let down = |class| dependencies.iter().any(|dependency| dependency.class == class && dependency.status == DependencyStatus::Down);
+        let status = if down(DependencyClass::Hard) {
+            ReadinessStatus::NotReady
+        } else if down(DependencyClass::Soft) {
+            ReadinessStatus::Degraded
+        } else {
+            ReadinessStatus::Ready
+        };
+        let warnings = dependencies
+            .iter()
+            .filter(|dependency| dependency.class == DependencyClass::Soft && dependency.status == DependencyStatus::Down)
+            .map(|dependency| format!("{} is down: {}", dependency.name, dependency.error.as_deref().unwrap_or("unknown error")))
+            .collect();
+
+        ReadinessReport {
+            status,
+            checked_at: Utc::now(),
+            dependencies,
+            warnings,
+        }
+    }
+}
+
+/// Log each dependency whose status changed since `previous`, and the overall status if it changed.
+/// On the first report only dependencies that are down are logged.
+fn log_transitions(previous: Option<&ReadinessReport>, report: &ReadinessReport) {
+    for dependency in &report.dependencies {
+        let before = previous.and_then(|previous| previous.dependencies.iter().find(|d| d.name == dependency.name)).map(|d| d.status);
+        match (before, dependency.status) {
+            (Some(before), after) if before == after => {}
+            (None, DependencyStatus::Up) => {}
+            (_, DependencyStatus::Down) => warn!(
+                dependency = %dependency.name,
+                class = ?dependency.class,
+                error = dependency.error.as_deref().unwrap_or_default(),
+                "Dependency {} is down",
+                dependency.name
+            ),
+            (Some(_), DependencyStatus::Up) => info!(dependency = %dependency.name, "Dependency {} is up again", dependency.name),
+        }
+    }
+
+    match previous.map(|previous| previous.status) {
+        Some(before) if before == report.status => {}
+        before if report.status.is_ready() => info!("Readiness changed from {} to {}", before.map_or("unknown".to_string(), |s| s.to_string()), report.status),
+        before => warn!("Readiness changed from {} to {}", before.map_or("unknown".to_string(), |s| s.to_string()), report.status),
+    }
+}
+
+/// The runtime's VM service answers its health RPC as serving
+struct RuntimeProbe {
+    vm_client: VmClient,
+}
+
+#[async_trait]
+impl DependencyProbe for RuntimeProbe {
+    fn name(&self) -> &str {
+        "runtime"
+    }
+
+    fn default_class(&self) -> DependencyClass {
+        DependencyClass::Hard
+    }
+
+    async fn check(&self) -> Result<(), String> {
+        match self.vm_client.health_check().await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err("VM service is not serving".to_string()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// The runtime's database service answers its health RPC as serving
+struct DotDbProbe {
+    backend: RuntimeStateBackend,
+}
+
+#[async_trait]
+impl DependencyProbe for DotDbProbe {
+    fn name(&self) -> &str {
+        "dotdb"
+    }
+
+    fn default_class(&self) -> DependencyClass {
+        DependencyClass::Hard
+    }
+
+    async fn check(&self) -> Result<(), String> {
+        match self.backend.health_check().await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err("database service is not serving".to_string()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// Tokens signed with the configured secret validate
+struct AuthProbe {
+    auth_service: Arc<Mutex<AuthService>>,
+}
+
+#[async_trait]
+impl DependencyProbe for AuthProbe {
+    fn name(&self) -> &str {
+        "auth"
+    }
+
+    fn default_class(&self) -> DependencyClass {
+        DependencyClass::Hard
+    }
+
+    async fn check(&self) -> Result<(), String> {
+        self.auth_service.lock().await.self_check().map_err(|e| e.to_string())
+    }
+}
+
+/// A metrics recorder is installed, so gateway metrics are exported rather than dropped
+struct MetricsProbe;
+
+#[async_trait]
+impl DependencyProbe for MetricsProbe {
+    fn name(&self) -> &str {
+        "metrics"
+    }
+
+    fn default_class(&self) -> DependencyClass {
+        DependencyClass::Soft
+    }
+
+    async fn check(&self) -> Result<(), String> {
+        metrics::try_recorder().map(|_| ()).ok_or_else(|| "no metrics recorder is installed".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+    struct FakeProbe {
+        name: &'static str,
+        class: DependencyClass,
+        up: Arc<AtomicBool>,
+        delay: Duration,
+        checks: Arc<AtomicU64>,
+    }
+
+    impl FakeProbe {
+        fn new(name: &'static str, class: DependencyClass) -> Self {
+            Self {
+                name,
+                class,
+                up: Arc::new(AtomicBool::new(true)),
+                delay: Duration::ZERO,
+                checks: Arc::new(AtomicU64::new(0)),
+            }
+        }
+
+        fn down(self) -> Self {
+            self.up.store(false, Ordering::Relaxed);
+            self
+        }
+    }
+
+    #[async_trait]
+    impl DependencyProbe for FakeProbe {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn default_class(&self) -> DependencyClass {
+            self.class
+        }
+
+        async fn check(&self) -> Result<(), String> {
+            self.checks.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(self.delay).await;
+            if self.up.load(Ordering::Relaxed) { Ok(()) } else { Err("refused".to_string()) }
+        }
+    }
+
+    fn dependency<'a>(report: &'a ReadinessReport, name: &str) -> &'a DependencyReport {
+        report.dependencies.iter().find(|dependency| dependency.name == name).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_hard_dependency_down_is_not_ready() {
+        let checker = ReadinessChecker::new(ReadinessConfig::default())
+            .with_probe(FakeProbe::new("runtime", DependencyClass::Hard).down())
+            .with_probe(FakeProbe::new("metrics", DependencyClass::Soft));
+
+        let report = checker.check().await;
+        assert_eq!(report.status, ReadinessStatus::NotReady);
+        assert!(!report.status.is_ready());
+        assert_eq!(dependency(&report, "runtime").status, DependencyStatus::Down);
+        assert_eq!(dependency(&report, "runtime").error.as_deref(), Some("refused"));
+        assert_eq!(dependency(&report, "metrics").status, DependencyStatus::Up);
+    }
+
+    #[tokio::test]
+    async fn test_soft_dependency_down_is_ready_with_warning() {
+        let checker = ReadinessChecker::new(ReadinessConfig::default())
+            .with_probe(FakeProbe::new("runtime", DependencyClass::Hard))
+            .with_probe(FakeProbe::new("metrics", DependencyClass::Soft).down());
+
+        let report = checker.check().await;
+        assert_eq!(report.status, ReadinessStatus::Degraded);
+        assert!(report.status.is_ready());
+        assert_eq!(report.warnings, vec!["metrics is down: refused".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_configured_class_overrides_default() {
+        let config = ReadinessConfig::default().with_class("runtime", DependencyClass::Soft).with_class("metrics", DependencyClass::Hard);
+        let checker = ReadinessChecker::new(config)
+            .with_probe(FakeProbe::new("runtime", DependencyClass::Hard).down())
+            .with_probe(FakeProbe::new("metrics", DependencyClass::Soft));
+
+        let report = checker.check().await;
+        assert_eq!(report.status, ReadinessStatus::Degraded);
+        assert_eq!(dependency(&report, "runtime").class, DependencyClass::Soft);
+        assert_eq!(dependency(&report, "metrics").class, DependencyClass::Hard);
+    }
+
+    #[tokio::test]
+    async fn test_slow_dependency_counts_as_down() {
+        let config = ReadinessConfig {
+            probe_timeout_ms: 50,
+            ..ReadinessConfig::default()
+        };
+        let mut slow = FakeProbe::new("dotdb", DependencyClass::Hard);
+        slow.delay = Duration::from_secs(5);
+        let checker = ReadinessChecker::new(config).with_probe(slow);
+
+        let report = checker.check().await;
+        assert_eq!(report.status, ReadinessStatus::NotReady);
+        assert_eq!(dependency(&report, "dotdb").error.as_deref(), Some("no answer within 50ms"));
+    }
+
+    #[tokio::test]
+    async fn test_reports_are_cached_for_ttl() {
+        let probe = FakeProbe::new("runtime", DependencyClass::Hard);
+        let (up, checks) = (probe.up.clone(), probe.checks.clone());
+        let config = ReadinessConfig {
+            cache_ttl_ms: 50,
+            ..ReadinessConfig::default()
+        };
+        let checker = ReadinessChecker::new(config).with_probe(probe);
+
+        assert_eq!(checker.check().await.status, ReadinessStatus::Ready);
+        up.store(false, Ordering::Relaxed);
+        assert_eq!(checker.check().await.status, ReadinessStatus::Ready);
+        assert_eq!(checks.load(Ordering::Relaxed), 1);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(checker.check().await.status, ReadinessStatus::NotReady);
+        assert_eq!(checks.load(Ordering::Relaxed), 2);
+    }
+}
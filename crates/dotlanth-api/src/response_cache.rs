@@ -0,0 +1,555 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Response cache for read-heavy GET routes
+//!
+//! Successful responses of the routes listed in [`ResponseCacheConfig::routes`] are
+//! kept per request path, query and auth scope, so a caller is only served
+//! responses the handler already produced for the same subject and permissions.
+//! Every cached response carries a strong ETag: the state version for dot state,
+//! otherwise a digest of the body. A matching `If-None-Match` gets a 304.
+//!
+//! Entries expire after their route's TTL and are dropped earlier when a dot event
+//! (deploy, delete, state change, completed execution) arrives for their dot.
+//! Routes without a dot, such as the dot list, are dropped on every such event.
+
+use crate::auth::Claims;
+use crate::grpc_pool::parse_env;
+use crate::openapi::{self, RouteSpec};
+use crate::transactions::TRANSACTION_HEADER;
+use crate::vm::VmClient;
+use dashmap::DashMap;
+use futures::StreamExt;
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, HeaderValue};
+use hyper::{Method, Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+
+/// Response header telling whether the response came from the cache
+pub const CACHE_STATUS_HEADER: &str = "x-cache";
+
+/// Delay before resubscribing after the dot event stream drops
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(5);
+
+/// Prefix of the routes whose responses dot events and mutations make stale
+const DOTS_PATH_PREFIX: &str = "/api/v1/vm/dots";
+
+/// Response cache settings
+#[derive(Debug, Clone)]
+pub struct ResponseCacheConfig {
+    pub enabled: bool,
+    /// Cacheable GET routes by path template below the version prefix, e.g.
+    /// `/vm/dots/{id}/state`, with their TTL. Routes not listed are never cached.
+    pub routes: HashMap<String, Duration>,
+    /// Entries held before further responses are served uncached
+    pub max_entries: usize,
+}
+
+impl Default for ResponseCacheConfig {
+    fn default() -> Self {
+        let ttl = Duration::from_secs(30);
+        Self {
+            enabled: true,
+            routes: [("/vm/dots".to_string(), ttl), ("/vm/dots/{id}/state".to_string(), ttl)].into_iter().collect(),
+            max_entries: 10_000,
+        }
+    }
+}
+
+impl ResponseCacheConfig {
+    /// Load response cache settings from `DOTLANTH_RESPONSE_CACHE_*` variables
+    ///
+    /// `DOTLANTH_RESPONSE_CACHE_ROUTES` replaces the cacheable routes with a comma
+    /// separated list of `path[=ttl_secs]`; routes without a TTL use
+    /// `DOTLANTH_RESPONSE_CACHE_TTL_SECS` and a TTL of 0 leaves the route uncached.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Some(enabled) = parse_env("DOTLANTH_RESPONSE_CACHE_ENABLED") {
+            config.enabled = enabled;
+        }
+        let default_ttl = parse_env("DOTLANTH_RESPONSE_CACHE_TTL_SECS").map(Duration::from_secs);
+        match parse_env::<String>("DOTLANTH_RESPONSE_CACHE_ROUTES") {
+            Some(routes) => config.routes = parse_routes(&routes, default_ttl.unwrap_or(Duration::from_secs(30))),
+            None => {
+                if let Some(ttl) = default_ttl {
+                    config.routes.values_mut().for_each(|route_ttl| *route_ttl = ttl);
+                    config.routes.retain(|_, ttl| !ttl.is_zero());
+                }
+            }
+        }
+        if let Some(max_entries) = parse_env::<usize>("DOTLANTH_RESPONSE_CACHE_MAX_ENTRIES").filter(|&max_entries| max_entries > 0) {
+            config.max_entries = max_entries;
+        }
+
+        config
+    }
+
+    /// TTL of a route, or `None` if its responses are not cached
+    fn ttl(&self, route: &RouteSpec) -> Option<Duration> {
+        if !self.enabled || route.method != Method::GET {
+            return None;
+        }
+        self.routes.get(route.path).copied()
+    }
+}
+
+/// Parse a `path[=ttl_secs],...` route list, skipping malformed entries
+fn parse_routes(routes: &str, default_ttl: Duration) -> HashMap<String, Duration> {
+    routes
+        .split(',')
+        .map(str::trim)
+        .filter(|route| !route.is_empty())
+        .filter_map(|route| match route.split_once('=') {
+            Some((path, ttl)) => match ttl.trim().parse() {
+                Ok(secs) => Some((path.trim().to_string(), Duration::from_secs(secs))),
+                Err(_) => {
+                    warn!("Ignoring response cache route with an invalid TTL: {}", route);
+                    None
+                }
+            },
+            None => Some((route.to_string(), default_ttl)),
+        })
+        .filter(|(_, ttl)| !ttl.is_zero())
+        .collect()
+}
+
+/// Where a cacheable request's response is stored
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheKey {
+    key: String,
+    /// Dot the response describes; `None` for routes spanning all dots
+    dot_id: Option<String>,
+    ttl: Duration,
+}
+
+/// A cached response body with the headers it is served with
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    body: Bytes,
+    content_type: Option<HeaderValue>,
+    etag: String,
+}
+
+impl CachedResponse {
+    fn new(body: Bytes, content_type: Option<HeaderValue>) -> Self {
+        let etag = compute_etag(&body);
+        Self { body, content_type, etag }
+    }
+
+    pub fn etag(&self) -> &str {
+        &self.etag
+    }
+
+    /// Build the response, a bodiless 304 if `if_none_match` lists this response's ETag
+    pub fn respond(&self, if_none_match: Option<&HeaderValue>, hit: bool) -> Response<Full<Bytes>> {
+        let not_modified = if_none_match.and_then(|value| value.to_str().ok()).is_some_and(|value| etag_matches(value, &self.etag));
+
+        let mut builder = Response::builder()
+            .header(ETAG, &self.etag)
+            // Responses depend on the caller's token, so shared caches must not keep them
+            .header(CACHE_CONTROL, "private, no-cache")
+            .header(CACHE_STATUS_HEADER, if hit { "HIT" } else { "MISS" });
+        let body = if not_modified {
+            builder = builder.status(StatusCode::NOT_MODIFIED);
+            Bytes::new()
+        } else {
+            builder = builder.status(StatusCode::OK);
+            if let Some(content_type) = &self.content_type {
+                builder = builder.header(CONTENT_TYPE, content_type);
+            }
+            self.body.clone()
+        };
+        builder.body(Full::new(body)).expect("cached response headers are valid")
+    }
+}
+
+/// Strong ETag of a response body
+///
+/// Dot state carries a version the runtime bumps on every change, which is used
+/// directly; other bodies are identified by their SHA-256.
+fn compute_etag(body: &[u8]) -> String {
+    #[derive(Deserialize)]
+    struct Versioned {
+        #[allow(dead_code)]
+        dot_id: String,
+        version: u64,
+    }
+
+    match serde_json::from_slice::<Versioned>(body) {
+        Ok(state) => format!("\"v{}\"", state.version),
+        Err(_) => {
+            let digest = ring::digest::digest(&ring::digest::SHA256, body);
+            let hex: String = digest.as_ref()[..16].iter().map(|b| format!("{:02x}", b)).collect();
+            format!("\"{}\"", hex)
+        }
+    }
+}
+
+/// Whether an `If-None-Match` header value matches `etag`, using weak comparison as RFC 9110 requires
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match.split(',').map(str::trim).any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+struct CacheEntry {
+    response: CachedResponse,
+    dot_id: Option<String>,
+    expires_at: Instant,
+}
+
+/// Cache counters since startup
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+pub struct ResponseCacheCounters {
+    pub hits: u64,
+    pub misses: u64,
+    /// Requests answered with 304 Not Modified, whether hits or misses
+    pub not_modified: u64,
+    /// Entries dropped by dot events or mutating requests before they expired
+    pub invalidations: u64,
+    pub entries: usize,
+}
+
+/// Cache of GET responses keyed by route and auth scope
+pub struct ResponseCache {
+    config: ResponseCacheConfig,
+    entries: DashMap<String, CacheEntry>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    not_modified: AtomicU64,
+    invalidations: AtomicU64,
+}
+
+impl ResponseCache {
+    pub fn new(config: ResponseCacheConfig) -> Self {
+        Self {
+            config,
+            entries: DashMap::new(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            not_modified: AtomicU64::new(0),
+            invalidations: AtomicU64::new(0),
+        }
+    }
+
+    pub fn config(&self) -> &ResponseCacheConfig {
+        &self.config
+    }
+
+    /// Cache key of a request, or `None` if its route is not cacheable
+    ///
+    /// Requests made within a transaction read its uncommitted writes and are never cached.
+    pub fn key<B>(&self, req: &Request<B>) -> Option<CacheKey> {
+        if req.headers().contains_key(TRANSACTION_HEADER) {
+            return None;
+        }
+        let path = req.uri().path();
+        let route = openapi::find_route(req.method(), path)?;
+        let ttl = self.config.ttl(route)?;
+
+        // Permissions are part of the scope so a token losing one is not served
+        // responses its handler would now refuse
+        let scope = match req.extensions().get::<Claims>() {
+            Some(claims) => {
+                let mut permissions = claims.permissions.clone();
+                permissions.sort();
+                format!("{}:{}", claims.sub, permissions.join(","))
+            }
+            None => String::new(),
+        };
+
+        Some(CacheKey {
+            key: format!("{}\n{}?{}", scope, path, req.uri().query().unwrap_or("")),
+            dot_id: path_param(route, path, "{id}"),
+            ttl,
+        })
+    }
+
+    /// Get an unexpired response, counting the hit or miss
+    pub fn get(&self, key: &CacheKey) -> Option<CachedResponse> {
+        let cached = self.entries.get(&key.key).filter(|entry| entry.expires_at > Instant::now()).map(|entry| entry.response.clone());
+        let counter = if cached.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        cached
+    }
+
+    /// Store a response body, returning it ready to serve. When the cache is full
+    /// and nothing has expired the response is returned without being stored.
+    pub fn insert(&self, key: CacheKey, body: Bytes, content_type: Option<HeaderValue>) -> CachedResponse {
+        let response = CachedResponse::new(body, content_type);
+
+        if self.entries.len() >= self.config.max_entries && !self.entries.contains_key(&key.key) {
+            let now = Instant::now();
+            self.entries.retain(|_, entry| entry.expires_at > now);
+            if self.entries.len() >= self.config.max_entries {
+                debug!("Response cache is full, not caching {}", key.key);
+                return response;
+            }
+        }
+
+        self.entries.insert(
+            key.key,
+            CacheEntry {
+                response: response.clone(),
+                dot_id: key.dot_id,
+                expires_at: Instant::now() + key.ttl,
+            },
+        );
+        response
+    }
+
+    /// Count a 304 sent in place of a response
+    pub fn record_not_modified(&self) {
+        self.not_modified.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Drop the responses describing a dot and those spanning all dots; returns how many were dropped
+    pub fn invalidate_dot(&self, dot_id: &str) -> usize {
+        self.remove_where(|entry| entry.dot_id.as_deref().is_none_or(|id| id == dot_id))
+    }
+
+    /// Drop every cached response; returns how many were dropped
+    pub fn invalidate_all(&self) -> usize {
+        self.remove_where(|_| true)
+    }
+
+    fn remove_where(&self, mut stale: impl FnMut(&CacheEntry) -> bool) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|_, entry| !stale(entry));
+        let removed = before.saturating_sub(self.entries.len());
+        self.invalidations.fetch_add(removed as u64, Ordering::Relaxed);
+        removed
+    }
+
+    /// Drop the responses a successful mutating request made stale, without waiting
+    /// for its dot event. Requests that name no dot, such as deploys, drop everything.
+    pub fn invalidate_after(&self, method: &Method, path: &str) {
+        if *method == Method::GET || !path.starts_with(DOTS_PATH_PREFIX) {
+            return;
+        }
+        match openapi::find_route(method, path).and_then(|route| path_param(route, path, "{id}")) {
+            Some(dot_id) => self.invalidate_dot(&dot_id),
+            None => self.invalidate_all(),
+        };
+    }
+
+    pub fn counters(&self) -> ResponseCacheCounters {
+        ResponseCacheCounters {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            not_modified: self.not_modified.load(Ordering::Relaxed),
+            invalidations: self.invalidations.load(Ordering::Relaxed),
+            entries: self.entries.len(),
+        }
+    }
+
+    /// Subscribe to dot events and drop the responses they make stale. Events may be
+    /// missed while the stream is down, so everything is dropped when it resubscribes.
+    /// The task exits once the cache is dropped.
+    pub fn spawn_invalidation_listener(self: &Arc<Self>, vm_client: VmClient) -> JoinHandle<()> {
+        let cache: Weak<Self> = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut resubscribing = false;
+            loop {
+                match vm_client.stream_all_dot_events(vec![]).await {
+                    Ok(mut events) => {
+                        info!("Listening for dot events to invalidate cached responses");
+                        if resubscribing && let Some(cache) = cache.upgrade() {
+                            cache.invalidate_all();
+                        }
+                        while let Some(event) = events.next().await {
+                            let Some(cache) = cache.upgrade() else {
+                                return;
+                            };
+                            match event {
+                                Ok(event) if invalidates_responses(&event.event_type) => {
+                                    let removed = cache.invalidate_dot(&event.dot_id);
+                                    if removed > 0 {
+                                        debug!("Invalidated {} cached responses for dot {} after {} event", removed, event.dot_id, event.event_type);
+                                    }
+                                }
+                                Ok(_) => {}
+                                Err(e) => {
+                                    warn!("Dot event stream failed: {}", e);
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => warn!("Failed to subscribe to dot events for response cache invalidation: {}", e),
+                }
+
+                if cache.strong_count() == 0 {
+                    return;
+                }
+                resubscribing = true;
+                tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+            }
+        })
+    }
+}
+
+/// Whether a dot event may change the responses cached for the dot
+fn invalidates_responses(event_type: &str) -> bool {
+    let event_type = event_type.to_ascii_lowercase();
+    ["deploy", "delete", "state", "abi", "completed"].iter().any(|kind| event_type.contains(kind))
+}
+
+/// Percent-decoded value of the `param` segment of `route` in `path`
+fn path_param(route: &RouteSpec, path: &str, param: &str) -> Option<String> {
+    let template = route.full_path();
+    let index = template.split('/').position(|segment| segment == param)?;
+    let segment = path.split('/').nth(index)?;
+    percent_encoding::percent_decode_str(segment).decode_utf8().ok().map(|id| id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(path: &str, claims: Option<Claims>) -> Request<()> {
+        let mut req = Request::builder().method(Method::GET).uri(path).body(()).unwrap();
+        if let Some(claims) = claims {
+            req.extensions_mut().insert(claims);
+        }
+        req
+    }
+
+    fn claims(sub: &str, permissions: &[&str]) -> Claims {
+        Claims::new(sub.to_string(), vec![], permissions.iter().map(|p| p.to_string()).collect(), chrono::Duration::hours(1))
+    }
+
+    #[test]
+    fn test_parse_routes() {
+        let routes = parse_routes("/vm/dots, /vm/dots/{id}/state=5, /vm/status=0, /bad=x", Duration::from_secs(30));
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes["/vm/dots"], Duration::from_secs(30));
+        assert_eq!(routes["/vm/dots/{id}/state"], Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_keys_only_for_cacheable_routes_and_per_scope() {
+        let cache = ResponseCache::new(ResponseCacheConfig::default());
+        let alice = claims("alice", &["execute:dots"]);
+
+        assert!(cache.key(&request("/api/v1/vm/status", Some(alice.clone()))).is_none());
+        assert!(cache.key(&request("/api/v1/vm/dots/abc/state/diff", Some(alice.clone()))).is_none());
+
+        let key = cache.key(&request("/api/v1/vm/dots/my%20dot/state", Some(alice.clone()))).unwrap();
+        assert_eq!(key.dot_id.as_deref(), Some("my dot"));
+        let mut in_transaction = request("/api/v1/vm/dots/my%20dot/state", Some(alice.clone()));
+        in_transaction.headers_mut().insert(TRANSACTION_HEADER, HeaderValue::from_static("abc"));
+        assert!(cache.key(&in_transaction).is_none());
+        assert_ne!(key, cache.key(&request("/api/v1/vm/dots/my%20dot/state", Some(claims("bob", &["execute:dots"])))).unwrap());
+        assert_ne!(key, cache.key(&request("/api/v1/vm/dots/my%20dot/state", Some(claims("alice", &[])))).unwrap());
+
+        let disabled = ResponseCache::new(ResponseCacheConfig {
+            enabled: false,
+            ..ResponseCacheConfig::default()
+        });
+        assert!(disabled.key(&request("/api/v1/vm/dots", Some(alice))).is_none());
+    }
+
+    #[test]
+    fn test_etags_and_conditional_requests() {
+        let state = CachedResponse::new(Bytes::from(r#"{"dot_id":"abc","status":"Active","version":7}"#), None);
+        assert_eq!(state.etag(), "\"v7\"");
+
+        let list = CachedResponse::new(Bytes::from("[]"), Some(HeaderValue::from_static("application/json")));
+        assert_eq!(list.etag().len(), 34);
+        assert_ne!(list.etag(), CachedResponse::new(Bytes::from("[1]"), None).etag());
+
+        let response = list.respond(None, false);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(response.headers()[CACHE_STATUS_HEADER], "MISS");
+
+        let if_none_match = HeaderValue::from_str(&format!("\"other\", W/{}", list.etag())).unwrap();
+        let response = list.respond(Some(&if_none_match), true);
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[ETAG], list.etag());
+        assert!(response.headers().get(CONTENT_TYPE).is_none());
+
+        assert!(etag_matches("*", "\"v1\""));
+        assert!(!etag_matches("\"v2\"", "\"v1\""));
+    }
+
+    #[test]
+    fn test_hits_misses_and_invalidation() {
+        let cache = ResponseCache::new(ResponseCacheConfig::default());
+        let alice = claims("alice", &["execute:dots"]);
+        let list = cache.key(&request("/api/v1/vm/dots", Some(alice.clone()))).unwrap();
+        let abc = cache.key(&request("/api/v1/vm/dots/abc/state", Some(alice.clone()))).unwrap();
+        let xyz = cache.key(&request("/api/v1/vm/dots/xyz/state", Some(alice))).unwrap();
+
+        assert!(cache.get(&list).is_none());
+        for key in [&list, &abc, &xyz] {
+            cache.insert(key.clone(), Bytes::from("[]"), None);
+        }
+        assert!(cache.get(&list).is_some());
+
+        assert_eq!(cache.invalidate_dot("abc"), 2);
+        assert!(cache.get(&abc).is_none());
+        assert!(cache.get(&xyz).is_some());
+
+        cache.invalidate_after(&Method::POST, "/api/v1/vm/dots/deploy");
+        assert_eq!(
+            cache.counters(),
+            ResponseCacheCounters {
+                hits: 2,
+                misses: 2,
+                not_modified: 0,
+                invalidations: 3,
+                entries: 0,
+            }
+        );
+
+        assert!(invalidates_responses("DotDeployed"));
+        assert!(invalidates_responses("state_changed"));
+        assert!(invalidates_responses("ExecutionCompleted"));
+        assert!(!invalidates_responses("ExecutionStarted"));
+    }
+
+    #[test]
+    fn test_expired_and_full() {
+        let cache = ResponseCache::new(ResponseCacheConfig {
+            routes: [("/vm/dots".to_string(), Duration::ZERO), ("/vm/dots/{id}/state".to_string(), Duration::from_secs(30))]
+                .into_iter()
+                .collect(),
+            max_entries: 1,
+            ..ResponseCacheConfig::default()
+        });
+        let expired = cache.key(&request("/api/v1/vm/dots", None)).unwrap();
+        let abc = cache.key(&request("/api/v1/vm/dots/abc/state", None)).unwrap();
+        let xyz = cache.key(&request("/api/v1/vm/dots/xyz/state", None)).unwrap();
+
+        cache.insert(expired.clone(), Bytes::from("[]"), None);
+        assert!(cache.get(&expired).is_none());
+
+        // The expired entry makes room for one more, after which the cache is full
+        cache.insert(abc.clone(), Bytes::from("{}"), None);
+        cache.insert(xyz.clone(), Bytes::from("{}"), None);
+        assert!(cache.get(&abc).is_some());
+        assert!(cache.get(&xyz).is_none());
+    }
+}
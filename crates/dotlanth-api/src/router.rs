@@ -16,25 +16,45 @@
 
 //! HTTP routing for the REST API
 
+use crate::admission::{AdmissionConfig, AdmissionController};
+use crate::api_keys::{API_KEY_HEADER, ApiKeyStore};
+use crate::audit::AuditLogger;
 use crate::auth::{AuthService, Claims, extract_token_from_header};
 use crate::db::DatabaseClient;
 use crate::error::{ApiError, ApiResult};
 use crate::gateway::{GatewayBridge, GatewayConfig};
 use crate::graphql::{AppSchema, build_schema};
-use crate::handlers::{auth, db, health, vm};
+use crate::handlers::abi_validation::{AbiCache, AbiValidationConfig};
+use crate::handlers::admin::CompatCheck;
+use crate::handlers::{admin, api_keys, auth, db, gateway, health, transactions, versioning, vm};
+use crate::openapi::{self, OPENAPI_JSON_PATH};
+use crate::rate_limiting::PriorityRateLimiter;
+use crate::readiness::{ReadinessChecker, ReadinessConfig};
+use crate::response_cache::{ResponseCache, ResponseCacheConfig};
+use crate::shutdown::Shutdown;
+use crate::telemetry::RequestCaller;
+use crate::uploads::{DeploymentTracker, UploadConfig};
+use crate::versioning::ApiVersions;
 use crate::vm::VmClient;
 use crate::websocket::WebSocketManager;
-use http_body_util::Full;
+use crate::websocket_mux::MultiplexConfig;
+use http_body_util::combinators::UnsyncBoxBody;
+use http_body_util::{BodyExt, Full};
 use hyper::body::Bytes;
+use hyper::header::{CONTENT_TYPE, IF_NONE_MATCH};
 use hyper::{Method, Request, Response, StatusCode};
 use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tracing::{error, info, warn};
-use utoipa::{
-    Modify, OpenApi,
-    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
-};
+
+/// Response body produced by the router
+///
+/// Most handlers return a buffered `Full` body which is boxed into this type;
+/// streaming endpoints (server-sent events) produce it directly.
+pub type RouterBody = UnsyncBoxBody<Bytes, Infallible>;
 
 /// HTTP router for the REST API
 pub struct Router {
@@ -44,25 +64,44 @@ pub struct Router {
     websocket_manager: Arc<WebSocketManager>,
     graphql_schema: AppSchema,
     openapi_spec: String,
+    /// Path the Swagger UI is served at, if enabled
+    docs_path: Option<String>,
     gateway_bridge: Arc<GatewayBridge>,
+    rate_limiter: Option<Arc<PriorityRateLimiter>>,
+    abi_cache: Arc<AbiCache>,
+    response_cache: Arc<ResponseCache>,
+    audit_logger: Option<Arc<AuditLogger>>,
+    api_keys: Option<Arc<ApiKeyStore>>,
+    compat_check: Option<Arc<CompatCheck>>,
+    api_versions: Arc<ApiVersions>,
+    upload_config: UploadConfig,
+    deployments: Arc<DeploymentTracker>,
+    admission: Arc<AdmissionController>,
+    readiness: Arc<ReadinessChecker>,
+    shutdown: Shutdown,
 }
 
 impl Router {
-    /// Create a new router
-    pub async fn new(auth_service: Arc<Mutex<AuthService>>, db_client: DatabaseClient, vm_client: VmClient) -> ApiResult<Self> {
+    /// Create a new router whose readiness and long-lived streams follow `shutdown`
+    pub async fn new(auth_service: Arc<Mutex<AuthService>>, db_client: DatabaseClient, vm_client: VmClient, shutdown: Shutdown) -> ApiResult<Self> {
         // Generate OpenAPI specification
-        let openapi_spec = generate_openapi_spec();
+        let openapi_spec = openapi::generate_spec();
 
         // Create WebSocket manager
-        let websocket_manager = Arc::new(WebSocketManager::new(vm_client.clone(), auth_service.clone()));
+        let websocket_manager = Arc::new(WebSocketManager::new(vm_client.clone(), auth_service.clone(), shutdown.clone()));
 
         // Build GraphQL schema
         let graphql_schema = build_schema(auth_service.clone(), db_client.clone(), vm_client.clone(), websocket_manager.clone());
 
         // Create gateway bridge
-        let gateway_config = GatewayConfig::default();
+        let gateway_config = GatewayConfig {
+            tls: vm_client.tls().clone(),
+            ..Default::default()
+        };
         let gateway_bridge = Arc::new(GatewayBridge::new(gateway_config, auth_service.clone()).await?);
 
+        let readiness = Arc::new(ReadinessChecker::for_gateway(ReadinessConfig::default(), &vm_client, auth_service.clone()));
+
         Ok(Self {
             auth_service,
             db_client,
@@ -70,34 +109,235 @@ impl Router {
             websocket_manager,
             graphql_schema,
             openapi_spec,
+            docs_path: Some("/docs".to_string()),
             gateway_bridge,
+            rate_limiter: None,
+            abi_cache: Arc::new(AbiCache::new(AbiValidationConfig::default())),
+            response_cache: Arc::new(ResponseCache::new(ResponseCacheConfig::default())),
+            audit_logger: None,
+            api_keys: None,
+            compat_check: None,
+            api_versions: Arc::new(ApiVersions::default()),
+            upload_config: UploadConfig::default(),
+            deployments: Arc::new(DeploymentTracker::default()),
+            admission: Arc::new(AdmissionController::new(AdmissionConfig::default())),
+            readiness,
+            shutdown,
         })
     }
 
-    /// Route a request to the appropriate handler
-    pub async fn route(&self, mut req: Request<hyper::body::Incoming>) -> Result<Response<Full<Bytes>>, ApiError> {
+    /// Expose the counters of the rate limiter applied in front of this router
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<PriorityRateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Validate execution inputs against dot ABIs with the given settings
+    pub fn with_abi_validation(mut self, config: AbiValidationConfig) -> Self {
+        self.abi_cache = Arc::new(AbiCache::new(config));
+        self
+    }
+
+    /// Cache GET responses with the given settings
+    pub fn with_response_cache(mut self, config: ResponseCacheConfig) -> Self {
+        self.response_cache = Arc::new(ResponseCache::new(config));
+        self
+    }
+
+    /// Apply the given limits to multiplexed WebSocket channels
+    pub fn with_websocket_multiplexing(self, config: MultiplexConfig) -> Self {
+        self.websocket_manager.multiplexer().set_config(config);
+        self
+    }
+
+    /// Record mutating requests in the audit log and serve it to admins
+    pub fn with_audit_logger(mut self, audit_logger: Arc<AuditLogger>) -> Self {
+        self.audit_logger = Some(audit_logger);
+        self
+    }
+
+    /// Accept API keys from this store in place of a bearer token, and serve the key management endpoints
+    pub fn with_api_keys(mut self, api_keys: Arc<ApiKeyStore>) -> Self {
+        self.api_keys = Some(api_keys);
+        self
+    }
+
+    /// Let admins run the built-in contract cases against the gateway listening on `address`
+    pub fn with_compat_check(mut self, address: SocketAddr) -> Self {
+        self.compat_check = Some(Arc::new(CompatCheck::new(address)));
+        self
+    }
+
+    /// Apply the given limits to dot deployments uploaded as multipart bodies
+    pub fn with_deploy_uploads(mut self, config: UploadConfig) -> Self {
+        self.deployments = Arc::new(DeploymentTracker::new(config.status_retention));
+        self.upload_config = config;
+        self
+    }
+
+    /// Limit and queue dot executions with the given settings
+    pub fn with_admission_control(mut self, config: AdmissionConfig) -> Self {
+        self.admission = Arc::new(AdmissionController::new(config));
+        self
+    }
+
+    /// Probe dependencies for `/readyz` with the given settings
+    pub fn with_readiness(mut self, config: ReadinessConfig) -> Self {
+        self.readiness = Arc::new(ReadinessChecker::for_gateway(config, &self.vm_client, self.auth_service.clone()));
+        self
+    }
+
+    /// Serve the given API path versions
+    pub fn with_api_versions(mut self, api_versions: Arc<ApiVersions>) -> Self {
+        self.api_versions = api_versions;
+        self
+    }
+
+    /// Serve the Swagger UI at `path`, or not at all when disabled. The OpenAPI document
+    /// itself is always served at `/openapi.json`.
+    pub fn with_docs(mut self, enabled: bool, path: impl Into<String>) -> Self {
+        self.docs_path = enabled.then(|| path.into().trim_end_matches('/').to_string()).filter(|path| !path.is_empty());
+        self
+    }
+
+    /// ABI cache used to validate execution requests
+    pub fn abi_cache(&self) -> Arc<AbiCache> {
+        self.abi_cache.clone()
+    }
+
+    /// Cache of GET responses served in front of the buffered handlers
+    pub fn response_cache(&self) -> Arc<ResponseCache> {
+        self.response_cache.clone()
+    }
+
+    /// Admission control applied to dot executions
+    pub fn admission(&self) -> Arc<AdmissionController> {
+        self.admission.clone()
+    }
+
+    /// Route a request to the appropriate handler under the API version it asks for
+    pub async fn route(&self, mut req: Request<hyper::body::Incoming>) -> Result<Response<RouterBody>, ApiError> {
+        let Some(version) = self.api_versions.resolve(&mut req)? else {
+            return self.route_audited(req).await;
+        };
+
+        let mut response = self.route_audited(req).await?;
+        self.api_versions.apply_headers(version, response.headers_mut());
+        Ok(response)
+    }
+
+    /// Route a request to its handler, auditing it if it mutates state
+    async fn route_audited(&self, req: Request<hyper::body::Incoming>) -> Result<Response<RouterBody>, ApiError> {
+        let Some((audit_logger, mut pending)) = self.audit_logger.as_ref().and_then(|logger| logger.begin(&req).map(|pending| (logger, pending))) else {
+            return self.dispatch(self.authenticate(req).await?).await;
+        };
+
+        let result = match self.authenticate(req).await {
+            Ok(mut req) => {
+                pending.attach(&mut req);
+                self.dispatch(req).await
+            }
+            Err(e) => Err(e),
+        };
+        let status = match &result {
+            Ok(response) => response.status(),
+            Err(e) => e.status_code(),
+        };
+        audit_logger.finish(pending, status);
+        result
+    }
+
+    /// Dispatch an authenticated request, forwarding its caller with the gRPC calls made for it
+    async fn dispatch(&self, req: Request<hyper::body::Incoming>) -> Result<Response<RouterBody>, ApiError> {
+        let Some(claims) = req.extensions().get::<Claims>() else {
+            return self.dispatch_to_handler(req).await;
+        };
+        let caller = RequestCaller {
+            principal: claims.sub.clone(),
+            roles: claims.roles.clone(),
+        };
+        caller.scope(self.dispatch_to_handler(req)).await
+    }
+
+    async fn dispatch_to_handler(&self, req: Request<hyper::body::Incoming>) -> Result<Response<RouterBody>, ApiError> {
+        let path = req.uri().path().to_string();
+        let path_segments: Vec<&str> = path.split('/').collect();
+
+        // Streaming endpoints bypass the buffered handlers
+        if let (&Method::GET, ["", "api", "v1", "vm", "dots", id, "events"]) = (req.method(), path_segments.as_slice()) {
+            let query_params = parse_query_params(req.uri().query().unwrap_or(""));
+            return vm::stream_dot_events(req, id.to_string(), query_params, self.vm_client.clone(), self.shutdown.clone()).await;
+        }
+
+        self.route_cached(req).await.map(|response| response.map(BodyExt::boxed_unsync))
+    }
+
+    /// Serve cacheable GET routes from the response cache, filling it on a miss
+    async fn route_cached(&self, req: Request<hyper::body::Incoming>) -> Result<Response<Full<Bytes>>, ApiError> {
+        let Some(key) = self.response_cache.key(&req) else {
+            let (method, path) = (req.method().clone(), req.uri().path().to_string());
+            let response = self.route_buffered(req).await?;
+            if response.status().is_success() {
+                self.response_cache.invalidate_after(&method, &path);
+            }
+            return Ok(response);
+        };
+
+        let if_none_match = req.headers().get(IF_NONE_MATCH).cloned();
+        let (cached, hit) = match self.response_cache.get(&key) {
+            Some(cached) => (cached, true),
+            None => {
+                let response = self.route_buffered(req).await?;
+                if response.status() != StatusCode::OK {
+                    return Ok(response);
+                }
+                let (parts, body) = response.into_parts();
+                let body = body.collect().await.unwrap_or_else(|e| match e {}).to_bytes();
+                (self.response_cache.insert(key, body, parts.headers.get(CONTENT_TYPE).cloned()), false)
+            }
+        };
+
+        let response = cached.respond(if_none_match.as_ref(), hit);
+        if response.status() == StatusCode::NOT_MODIFIED {
+            self.response_cache.record_not_modified();
+        }
+        Ok(response)
+    }
+
+    /// Validate the bearer token or API key for protected paths and attach the claims to the request
+    async fn authenticate(&self, mut req: Request<hyper::body::Incoming>) -> Result<Request<hyper::body::Incoming>, ApiError> {
         let path = req.uri().path().to_string();
         let method = req.method().clone();
 
         info!("Routing request: {} {}", method, path);
 
-        // Public paths that don't require authentication
-        let public_paths = [
-            "/api/v1/health",
-            "/api/v1/version",
-            "/api/v1/auth/login",
-            "/docs",
-            "/docs/",
-            "/api-docs",
-            "/openapi.json",
-            "/graphql",
-            "/playground",
-        ];
-
-        // Check if authentication is required
-        let requires_auth = !public_paths.iter().any(|public_path| path.as_str() == *public_path || path.starts_with(&format!("{}/", public_path)));
+        // Registered routes declare whether they need a token; the remaining public
+        // paths serve documentation, GraphQL, which authorizes per field, and probes
+        let public_paths = ["/api-docs", OPENAPI_JSON_PATH, "/graphql", "/playground", "/versions", "/livez", "/readyz"];
+        let requires_auth = match openapi::find_route(&method, &path) {
+            Some(route) => route.auth,
+            None => {
+                let is_docs = self.docs_path.as_deref().is_some_and(|docs| path == docs || path.starts_with(&format!("{}/", docs)));
+                !is_docs && !public_paths.iter().any(|public_path| path.as_str() == *public_path || path.starts_with(&format!("{}/", public_path)))
+            }
+        };
 
         if requires_auth {
+            // A request without a bearer token may present an API key, limited to its scopes and dots
+            if let Some(api_keys) = &self.api_keys
+                && !req.headers().contains_key("authorization")
+                && let Some(secret) = req.headers().get(API_KEY_HEADER)
+            {
+                let secret = secret.to_str().map_err(|_| ApiError::Unauthorized {
+                    message: "Invalid API key header encoding".to_string(),
+                })?;
+                let key = api_keys.authenticate(secret).await?;
+                key.authorize(&method, &path)?;
+                req.extensions_mut().insert(key.claims());
+                req.extensions_mut().insert(key);
+                return Ok(req);
+            }
+
             // Extract and validate JWT token
             if let Some(auth_header) = req.headers().get("authorization") {
                 match auth_header.to_str() {
@@ -141,6 +381,14 @@ impl Router {
             }
         }
 
+        Ok(req)
+    }
+
+    /// Route a request to a handler producing a buffered response
+    async fn route_buffered(&self, req: Request<hyper::body::Incoming>) -> Result<Response<Full<Bytes>>, ApiError> {
+        let path = req.uri().path().to_string();
+        let method = req.method().clone();
+
         // Check for WebSocket upgrade request
         if method == Method::GET && path.as_str() == "/api/v1/ws" {
             // Simple check for WebSocket upgrade request
@@ -149,11 +397,19 @@ impl Router {
             }
         }
 
+        // GraphQL subscriptions are served over WebSocket on the same path as queries
+        if method == Method::GET && path.as_str() == "/graphql" && req.headers().get("upgrade").and_then(|h| h.to_str().ok()).is_some_and(|h| h.eq_ignore_ascii_case("websocket")) {
+            return crate::graphql::ws::graphql_ws_upgrade(req, self.graphql_schema.clone(), self.auth_service.clone(), self.websocket_manager.clone()).await;
+        }
+
         // Simple path matching
         match (&method, path.as_str()) {
             // Health endpoints
             (&Method::GET, "/api/v1/health") => health::health_check(req, self.db_client.clone(), self.vm_client.clone()).await,
+            (&Method::GET, "/api/v1/ready") => health::readiness_check(req, &self.shutdown).await,
             (&Method::GET, "/api/v1/version") => health::version_info(req).await,
+            (&Method::GET, "/livez") => health::liveness_check(req).await,
+            (&Method::GET, "/readyz") => health::dependency_readiness(req, &self.readiness, &self.shutdown).await,
 
             // Auth endpoints
             (&Method::POST, "/api/v1/auth/login") => auth::login(req, self.auth_service.clone()).await,
@@ -162,8 +418,14 @@ impl Router {
             // Collections
             (&Method::GET, "/api/v1/collections") => db::list_collections(req, self.db_client.clone()).await,
 
+            // Transactions
+            (&Method::POST, "/api/v1/transactions") => transactions::begin_transaction(req, self.db_client.clone()).await,
+
+            // Read snapshots
+            (&Method::POST, "/api/v1/snapshots") => db::begin_snapshot(req, self.db_client.clone()).await,
+
             // VM endpoints
-            (&Method::POST, "/api/v1/vm/dots/deploy") => vm::deploy_dot(req, self.vm_client.clone()).await,
+            (&Method::POST, "/api/v1/vm/dots/deploy") => vm::deploy_dot(req, self.vm_client.clone(), self.abi_cache.clone(), self.deployments.clone(), self.upload_config.clone()).await,
             (&Method::GET, "/api/v1/vm/dots") => vm::list_dots(req, self.vm_client.clone()).await,
             (&Method::GET, "/api/v1/vm/status") => vm::get_vm_status(req, self.vm_client.clone()).await,
             (&Method::GET, "/api/v1/vm/architectures") => vm::get_architectures(req, self.vm_client.clone()).await,
@@ -173,12 +435,30 @@ impl Router {
             (&Method::POST, "/graphql") => self.handle_graphql(req).await,
 
             // Documentation
-            (&Method::GET, "/docs") | (&Method::GET, "/docs/") => self.serve_docs().await,
-            (&Method::GET, "/openapi.json") => self.serve_openapi_spec().await,
+            (&Method::GET, "/versions") => versioning::list_versions(self.api_versions.clone()).await,
+            (&Method::GET, OPENAPI_JSON_PATH) => self.serve_openapi_spec().await,
+            (&Method::GET, path) if self.docs_path.as_deref().is_some_and(|docs| path.trim_end_matches('/') == docs) => self.serve_docs().await,
 
             // Gateway bridge endpoints
-            (&Method::GET, "/api/v1/gateway/health") => self.gateway_health_check().await,
-            (&Method::GET, "/api/v1/gateway/metrics") => self.gateway_metrics().await,
+            (&Method::GET, "/api/v1/gateway/health") => gateway::gateway_health(self.gateway_bridge.clone()).await,
+            (&Method::GET, "/api/v1/gateway/metrics") => gateway::gateway_metrics(self.gateway_bridge.clone(), &self.vm_client).await,
+            (&Method::GET, "/api/v1/gateway/rate-limits") => gateway::rate_limit_counters(self.rate_limiter.clone()).await,
+            (&Method::GET, "/api/v1/gateway/cache") => gateway::response_cache_counters(self.response_cache.clone()).await,
+            (&Method::GET, "/api/v1/gateway/admission") => gateway::admission_counters(self.admission.clone()).await,
+            (&Method::GET, "/api/v1/gateway/config") => gateway::get_config(req, self.admission.clone()).await,
+            (&Method::PUT, "/api/v1/gateway/config") => gateway::update_config(req, self.admission.clone()).await,
+
+            // Admin endpoints
+            (&Method::GET, "/api/v1/admin/audit") => {
+                let query_params = parse_query_params(req.uri().query().unwrap_or(""));
+                admin::query_audit_log(req, query_params, self.audit_logger.clone()).await
+            }
+            (&Method::POST, "/api/v1/admin/compat-check") => {
+                let query_params = parse_query_params(req.uri().query().unwrap_or(""));
+                admin::run_compat_check(req, query_params, self.compat_check.clone()).await
+            }
+            (&Method::GET, "/api/v1/admin/api-keys") => api_keys::list_api_keys(req, self.api_keys.clone()).await,
+            (&Method::POST, "/api/v1/admin/api-keys") => api_keys::create_api_key(req, self.api_keys.clone()).await,
 
             // Dynamic routes with path parameters
             _ => self.handle_dynamic_routes(req).await,
@@ -205,20 +485,45 @@ impl Router {
             (&Method::POST, ["", "api", "v1", "collections", collection, "documents"]) => db::create_document(req, collection.to_string(), self.db_client.clone()).await,
 
             // Individual documents
-            (&Method::GET, ["", "api", "v1", "collections", collection, "documents", id]) => db::get_document(req, collection.to_string(), id.to_string(), self.db_client.clone()).await,
+            (&Method::GET, ["", "api", "v1", "collections", collection, "documents", id]) => {
+                let query_params = parse_query_params(&query);
+                db::get_document(req, collection.to_string(), id.to_string(), query_params, self.db_client.clone()).await
+            }
             (&Method::PUT, ["", "api", "v1", "collections", collection, "documents", id]) => db::update_document(req, collection.to_string(), id.to_string(), self.db_client.clone()).await,
             (&Method::DELETE, ["", "api", "v1", "collections", collection, "documents", id]) => db::delete_document(req, collection.to_string(), id.to_string(), self.db_client.clone()).await,
 
+            // Transactions
+            (&Method::POST, ["", "api", "v1", "transactions", id, "commit"]) => transactions::commit_transaction(req, id.to_string(), self.db_client.clone()).await,
+            (&Method::DELETE, ["", "api", "v1", "transactions", id]) => transactions::abort_transaction(req, id.to_string(), self.db_client.clone()).await,
+
+            // Read snapshots
+            (&Method::DELETE, ["", "api", "v1", "snapshots", token]) => db::end_snapshot(req, token.to_string(), self.db_client.clone()).await,
+
             // Search
             (&Method::GET, ["", "api", "v1", "collections", collection, "search"]) => {
                 let query_params = parse_query_params(&query);
                 db::search_documents(req, collection.to_string(), query_params, self.db_client.clone()).await
             }
 
+            // Aggregation
+            (&Method::POST, ["", "api", "v1", "collections", collection, "aggregate"]) => {
+                let query_params = parse_query_params(&query);
+                db::aggregate_documents(req, collection.to_string(), query_params, self.db_client.clone()).await
+            }
+
             // VM dots
             (&Method::GET, ["", "api", "v1", "vm", "dots", id, "state"]) => vm::get_dot_state(req, id.to_string(), self.vm_client.clone()).await,
-            (&Method::POST, ["", "api", "v1", "vm", "dots", id, "execute"]) => vm::execute_dot(req, id.to_string(), self.vm_client.clone()).await,
-            (&Method::DELETE, ["", "api", "v1", "vm", "dots", id]) => vm::delete_dot(req, id.to_string(), self.vm_client.clone()).await,
+            (&Method::GET, ["", "api", "v1", "vm", "dots", id, "state", "diff"]) => {
+                let query_params = parse_query_params(&query);
+                vm::get_state_diff(req, id.to_string(), query_params, self.vm_client.clone()).await
+            }
+            (&Method::POST, ["", "api", "v1", "vm", "dots", id, "execute"]) => vm::execute_dot(req, id.to_string(), self.vm_client.clone(), self.abi_cache.clone(), self.admission.clone()).await,
+            (&Method::DELETE, ["", "api", "v1", "vm", "dots", id]) => vm::delete_dot(req, id.to_string(), self.vm_client.clone(), self.abi_cache.clone()).await,
+            (&Method::GET, ["", "api", "v1", "vm", "deployments", upload_id]) => vm::get_deployment_status(req, upload_id.to_string(), self.deployments.clone()).await,
+
+            // API keys
+            (&Method::POST, ["", "api", "v1", "admin", "api-keys", id, "rotate"]) => api_keys::rotate_api_key(req, id.to_string(), self.api_keys.clone()).await,
+            (&Method::DELETE, ["", "api", "v1", "admin", "api-keys", id]) => api_keys::revoke_api_key(req, id.to_string(), self.api_keys.clone()).await,
 
             _ => {
                 warn!("Route not found: {} {}", method, path);
@@ -229,94 +534,6 @@ impl Router {
         }
     }
 
-    /*async fn handle_graphql_ws(&self, mut req: Request<hyper::body::Incoming>) -> Result<Response<Full<Bytes>>, ApiError> {
-        // Verify WebSocket upgrade headers
-        let key = match req.headers().get("sec-websocket-key").and_then(|v| v.to_str().ok()) {
-            Some(k) => k.to_string(),
-            None => {
-                return Err(ApiError::BadRequest {
-                    message: "Missing Sec-WebSocket-Key".to_string(),
-                });
-            }
-        };
-        let upgrade = req.headers().get("upgrade").and_then(|v| v.to_str().ok()).map(|v| v.eq_ignore_ascii_case("websocket")).unwrap_or(false);
-        let connection_upgrade = req
-            .headers()
-            .get("connection")
-            .and_then(|v| v.to_str().ok())
-            .map(|v| v.to_ascii_lowercase().contains("upgrade"))
-            .unwrap_or(false);
-        if !upgrade || !connection_upgrade {
-            return Err(ApiError::BadRequest {
-                message: "Expected WebSocket upgrade".to_string(),
-            });
-        }
-
-        // Compute Sec-WebSocket-Accept
-        use base64::{Engine as _, engine::general_purpose};
-        use sha1::{Digest, Sha1};
-        const WS_MAGIC: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
-        let mut hasher = Sha1::new();
-        hasher.update(format!("{}{}", key, WS_MAGIC).as_bytes());
-        let accept = general_purpose::STANDARD.encode(hasher.finalize());
-
-        // Build 101 Switching Protocols response
-        let response = Response::builder()
-            .status(StatusCode::SWITCHING_PROTOCOLS)
-            .header("Upgrade", "websocket")
-            .header("Connection", "Upgrade")
-            .header("Sec-WebSocket-Accept", accept)
-            .body(Full::new(Bytes::new()))?;
-
-        // Spawn task to handle upgraded connection
-        let schema = self.graphql_schema.clone();
-        tokio::spawn(async move {
-            match hyper::upgrade::on(&mut req).await {
-                Ok(upgraded) => {
-                    use futures::{SinkExt, StreamExt};
-                    use tokio_tungstenite::WebSocketStream;
-                    use tokio_tungstenite::tungstenite::protocol::Role;
-
-                    let io = hyper_util::rt::TokioIo::new(upgraded);
-                    let mut ws = WebSocketStream::from_raw_socket(io, Role::Server, None).await;
-
-                    // Reader adapter: map Text/Binary messages to Vec<u8>
-                    let (mut sink, mut stream) = ws.split();
-                    let mapped = stream.filter_map(|msg| async move {
-                        match msg {
-                            Ok(m) if m.is_text() => Some(Ok(m.into_data())),
-                            Ok(m) if m.is_binary() => Some(Ok(m.into_data())),
-                            Ok(m) if m.is_close() => None,
-                            Ok(_) => None, // ignore ping/pong
-                            Err(e) => {
-                                tracing::error!("WS read error: {}", e);
-                                None
-                            }
-                        }
-                    });
-
-                    // Build GraphQL WS server
-                    use async_graphql::http::WebSocketProtocols;
-                    let server = async_graphql::http::WebSocket::new(schema, mapped, WebSocketProtocols::GraphQLWS)
-                        .on_connection_init(|_payload| async move { Ok(async_graphql::Data::default()) })
-                        .on_send(|msg| async move {
-                            // Send as Text (GraphQL protocol frames are JSON)
-                            if let Err(e) = sink.send(tokio_tungstenite::tungstenite::Message::Text(String::from_utf8_lossy(&msg).to_string())).await {
-                                tracing::error!("WS write error: {}", e);
-                            }
-                        });
-
-                    if let Err(e) = server.serve().await {
-                        tracing::error!("GraphQL WS serve error: {}", e);
-                    }
-                }
-                Err(e) => tracing::error!("Upgrade error: {}", e),
-            }
-        });
-
-        Ok(response)
-    }*/
-
     /// Serve GraphiQL
     async fn serve_graphiql(&self) -> Result<Response<Full<Bytes>>, ApiError> {
         let html = async_graphql::http::GraphiQLSource::build().endpoint("/graphql").subscription_endpoint("/graphql").finish();
@@ -328,47 +545,10 @@ impl Router {
 
     /// Serve OpenAPI documentation
     async fn serve_docs(&self) -> Result<Response<Full<Bytes>>, ApiError> {
-        let swagger_ui_html = r#"
-<!DOCTYPE html>
-<html>
-<head>
-    <title>Dotlanth API Documentation</title>
-    <link rel="stylesheet" type="text/css" href="https://unpkg.com/swagger-ui-dist@4.15.5/swagger-ui.css" />
-    <style>
-        html { box-sizing: border-box; overflow: -moz-scrollbars-vertical; overflow-y: scroll; }
-        *, *:before, *:after { box-sizing: inherit; }
-        body { margin:0; background: #fafafa; }
-    </style>
-</head>
-<body>
-    <div id="swagger-ui"></div>
-    <script src="https://unpkg.com/swagger-ui-dist@4.15.5/swagger-ui-bundle.js"></script>
-    <script src="https://unpkg.com/swagger-ui-dist@4.15.5/swagger-ui-standalone-preset.js"></script>
-    <script>
-        window.onload = function() {
-            const ui = SwaggerUIBundle({
-                url: '/openapi.json',
-                dom_id: '#swagger-ui',
-                deepLinking: true,
-                presets: [
-                    SwaggerUIBundle.presets.apis,
-                    SwaggerUIStandalonePreset
-                ],
-                plugins: [
-                    SwaggerUIBundle.plugins.DownloadUrl
-                ],
-                layout: "StandaloneLayout"
-            });
-        };
-    </script>
-</body>
-</html>
-        "#;
-
         Ok(Response::builder()
             .status(StatusCode::OK)
             .header("content-type", "text/html")
-            .body(Full::new(Bytes::from(swagger_ui_html)))?)
+            .body(Full::new(Bytes::from(openapi::swagger_ui_html())))?)
     }
 
     async fn handle_graphql(&self, req: Request<hyper::body::Incoming>) -> Result<Response<Full<Bytes>>, ApiError> {
@@ -409,70 +589,6 @@ impl Router {
         &self.openapi_spec
     }
 
-    /// Gateway health check endpoint
-    async fn gateway_health_check(&self) -> Result<Response<Full<Bytes>>, ApiError> {
-        match self.gateway_bridge.health_check().await {
-            Ok(_) => {
-                let response = serde_json::json!({
-                    "status": "healthy",
-                    "timestamp": chrono::Utc::now().to_rfc3339(),
-                    "service": "gateway_bridge"
-                });
-
-                Ok(Response::builder()
-                    .status(StatusCode::OK)
-                    .header("content-type", "application/json")
-                    .body(Full::new(Bytes::from(serde_json::to_string(&response)?)))?)
-            }
-            Err(e) => {
-                let response = serde_json::json!({
-                    "status": "unhealthy",
-                    "error": e.to_string(),
-                    "timestamp": chrono::Utc::now().to_rfc3339(),
-                    "service": "gateway_bridge"
-                });
-
-                Ok(Response::builder()
-                    .status(StatusCode::SERVICE_UNAVAILABLE)
-                    .header("content-type", "application/json")
-                    .body(Full::new(Bytes::from(serde_json::to_string(&response)?)))?)
-            }
-        }
-    }
-
-    /// Gateway metrics endpoint
-    async fn gateway_metrics(&self) -> Result<Response<Full<Bytes>>, ApiError> {
-        let metrics = self.gateway_bridge.get_metrics().await;
-
-        let response = serde_json::json!({
-            "metrics": {
-                "total_requests": metrics.total_requests,
-                "successful_requests": metrics.successful_requests,
-                "failed_requests": metrics.failed_requests,
-                "avg_latency_ms": metrics.avg_latency_ms,
-                "active_streaming_connections": metrics.active_streaming_connections,
-                "protocol_conversions": metrics.protocol_conversions,
-                "error_rate": if metrics.total_requests > 0 {
-                    metrics.failed_requests as f64 / metrics.total_requests as f64
-                } else {
-                    0.0
-                },
-                "success_rate": if metrics.total_requests > 0 {
-                    metrics.successful_requests as f64 / metrics.total_requests as f64
-                } else {
-                    0.0
-                }
-            },
-            "timestamp": chrono::Utc::now().to_rfc3339(),
-            "service": "gateway_bridge"
-        });
-
-        Ok(Response::builder()
-            .status(StatusCode::OK)
-            .header("content-type", "application/json")
-            .body(Full::new(Bytes::from(serde_json::to_string(&response)?)))?)
-    }
-
     /// Get the gateway bridge instance
     pub fn gateway_bridge(&self) -> Arc<GatewayBridge> {
         self.gateway_bridge.clone()
@@ -493,90 +609,3 @@ fn parse_query_params(query: &str) -> HashMap<String, String> {
 
     params
 }
-
-/// Generate OpenAPI specification
-fn generate_openapi_spec() -> String {
-    #[derive(OpenApi)]
-    #[openapi(
-        paths(
-            // Health endpoints
-            health::health_check,
-            health::version_info,
-
-            // Auth endpoints
-            auth::login,
-            auth::get_profile,
-
-            // Database endpoints
-            db::list_collections,
-            db::create_collection,
-            db::delete_collection,
-            db::get_documents,
-            db::create_document,
-            db::get_document,
-            db::update_document,
-            db::delete_document,
-            db::search_documents,
-
-            // VM endpoints
-            vm::deploy_dot,
-            vm::get_dot_state,
-            vm::execute_dot,
-            vm::list_dots,
-            vm::delete_dot,
-            vm::get_vm_status,
-            vm::get_architectures,
-        ),
-        components(
-            schemas(
-                crate::models::TokenResponse,
-                crate::models::LoginRequest,
-                crate::models::UserProfile,
-                crate::models::Document,
-                crate::models::CreateDocumentRequest,
-                crate::models::UpdateDocumentRequest,
-                crate::models::CreateDocumentResponse,
-                crate::models::Collection,
-                crate::models::DocumentList,
-                crate::models::PaginationInfo,
-                crate::models::SearchResults,
-                crate::models::DeployDotRequest,
-                crate::models::DeployDotResponse,
-                crate::models::DotConfig,
-                crate::models::ExecuteDotRequest,
-                crate::models::ExecuteDotResponse,
-                crate::models::DotState,
-                crate::models::ExecutionContext,
-                crate::models::DotStatus,
-                crate::models::ExecutionStatus,
-                crate::models::ValidationResult,
-                crate::models::HealthResponse,
-                crate::models::ServiceStatus,
-                crate::models::ApiVersion,
-                crate::models::WebSocketMessage,
-                crate::models::DotEvent,
-            )
-        ),
-        tags(
-            (name = "Health", description = "Health check and version endpoints"),
-            (name = "Authentication", description = "Authentication and authorization endpoints"),
-            (name = "Database", description = "Database collection and document management"),
-            (name = "Virtual Machine", description = "VM dot deployment and execution"),
-            (name = "WebSocket", description = "WebSocket streaming for real-time events")
-        ),
-        modifiers(&SecurityAddon)
-    )]
-    struct ApiDoc;
-
-    struct SecurityAddon;
-
-    impl Modify for SecurityAddon {
-        fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
-            if let Some(components) = openapi.components.as_mut() {
-                components.add_security_scheme("bearer_auth", SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).bearer_format("JWT").build()))
-            }
-        }
-    }
-
-    ApiDoc::openapi().to_pretty_json().unwrap_or_else(|_| "{}".to_string())
-}
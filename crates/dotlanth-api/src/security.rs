@@ -24,6 +24,7 @@
 
 use crate::auth::{AuthService, Claims};
 use crate::error::{ApiError, ApiResult};
+use crate::grpc_pool::parse_env;
 use crate::rate_limiting::{RateLimitAlgorithm, RateLimitConfig, RateLimiterManager};
 use dashmap::DashMap;
 use http_body_util::{BodyExt, Full};
@@ -36,6 +37,7 @@ use std::collections::HashMap;
 use std::net::IpAddr;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
+use thiserror::Error;
 use tokio::sync::Mutex as TokioMutex;
 use tokio::sync::Mutex;
 use tower::{Layer, Service};
@@ -107,6 +109,135 @@ impl Default for SecurityConfig {
     }
 }
 
+/// Default Content-Security-Policy for gateway responses
+const DEFAULT_CONTENT_SECURITY_POLICY: &str =
+    "default-src 'self'; script-src 'self'; object-src 'none'; style-src 'self' 'unsafe-inline'; img-src 'self' data:; font-src 'self'; connect-src 'self'; media-src 'self';";
+
+/// Security headers added to every gateway response
+#[derive(Debug, Clone)]
+pub struct SecurityHeadersConfig {
+    pub enabled: bool,
+    /// `max-age` of Strict-Transport-Security in seconds; 0 omits the header
+    pub hsts_max_age_secs: u64,
+    /// Extend Strict-Transport-Security to subdomains
+    pub hsts_include_subdomains: bool,
+    /// X-Frame-Options value, `DENY` or `SAMEORIGIN`
+    pub frame_options: String,
+    /// Content-Security-Policy value; empty omits the header
+    pub content_security_policy: String,
+}
+
+impl Default for SecurityHeadersConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            hsts_max_age_secs: 31_536_000,
+            hsts_include_subdomains: true,
+            frame_options: "DENY".to_string(),
+            content_security_policy: DEFAULT_CONTENT_SECURITY_POLICY.to_string(),
+        }
+    }
+}
+
+impl SecurityHeadersConfig {
+    /// Load security header settings from `DOTLANTH_SECURITY_HEADERS_*` variables
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Some(enabled) = parse_env("DOTLANTH_SECURITY_HEADERS_ENABLED") {
+            config.enabled = enabled;
+        }
+        if let Some(max_age_secs) = parse_env("DOTLANTH_SECURITY_HEADERS_HSTS_MAX_AGE_SECS") {
+            config.hsts_max_age_secs = max_age_secs;
+        }
+        if let Some(include_subdomains) = parse_env("DOTLANTH_SECURITY_HEADERS_HSTS_INCLUDE_SUBDOMAINS") {
+            config.hsts_include_subdomains = include_subdomains;
+        }
+        if let Some(frame_options) = parse_env("DOTLANTH_SECURITY_HEADERS_FRAME_OPTIONS") {
+            config.frame_options = frame_options;
+        }
+        if let Some(policy) = parse_env("DOTLANTH_SECURITY_HEADERS_CSP") {
+            config.content_security_policy = policy;
+        }
+
+        config
+    }
+}
+
+/// A security header setting that cannot be sent
+#[derive(Debug, Error, PartialEq)]
+pub enum SecurityHeadersError {
+    #[error("invalid X-Frame-Options '{0}': expected DENY or SAMEORIGIN")]
+    InvalidFrameOptions(String),
+
+    #[error("invalid Content-Security-Policy: {0}")]
+    InvalidContentSecurityPolicy(String),
+}
+
+/// Validated security headers, built once and copied onto each response
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders {
+    headers: header::HeaderMap,
+}
+
+impl SecurityHeaders {
+    pub fn new(config: &SecurityHeadersConfig) -> Result<Self, SecurityHeadersError> {
+        let mut headers = header::HeaderMap::new();
+        if !config.enabled {
+            return Ok(Self { headers });
+        }
+
+        let frame_options = config.frame_options.to_ascii_uppercase();
+        if frame_options != "DENY" && frame_options != "SAMEORIGIN" {
+            return Err(SecurityHeadersError::InvalidFrameOptions(config.frame_options.clone()));
+        }
+        headers.insert(
+            header::X_FRAME_OPTIONS,
+            header::HeaderValue::from_str(&frame_options).expect("DENY and SAMEORIGIN are valid header values"),
+        );
+
+        if !config.content_security_policy.is_empty() {
+            let policy = header::HeaderValue::from_str(&config.content_security_policy).map_err(|e| SecurityHeadersError::InvalidContentSecurityPolicy(e.to_string()))?;
+            headers.insert(header::CONTENT_SECURITY_POLICY, policy);
+        }
+
+        if config.hsts_max_age_secs > 0 {
+            let subdomains = if config.hsts_include_subdomains { "; includeSubDomains" } else { "" };
+            headers.insert(
+                header::STRICT_TRANSPORT_SECURITY,
+                header::HeaderValue::from_str(&format!("max-age={}{}", config.hsts_max_age_secs, subdomains)).expect("HSTS value is ASCII"),
+            );
+        }
+
+        headers.insert(header::X_CONTENT_TYPE_OPTIONS, header::HeaderValue::from_static("nosniff"));
+        headers.insert(header::X_XSS_PROTECTION, header::HeaderValue::from_static("1; mode=block"));
+        headers.insert(header::REFERRER_POLICY, header::HeaderValue::from_static("strict-origin-when-cross-origin"));
+        headers.insert(
+            header::HeaderName::from_static("permissions-policy"),
+            header::HeaderValue::from_static(
+                "geolocation=(), midi=(), notifications=(), push=(), sync-xhr=(), microphone=(), camera=(), magnetometer=(), gyroscope=(), speaker=(), vibrate=(), fullscreen=(), payment=()",
+            ),
+        );
+
+        Ok(Self { headers })
+    }
+
+    /// Add the headers to a response, keeping any a handler already set
+    pub fn apply(&self, headers: &mut header::HeaderMap) {
+        for (name, value) in &self.headers {
+            if !headers.contains_key(name) {
+                headers.insert(name.clone(), value.clone());
+            }
+        }
+    }
+}
+
+impl Default for SecurityHeaders {
+    fn default() -> Self {
+        Self::new(&SecurityHeadersConfig::default()).expect("default security headers are valid")
+    }
+}
+
 /// API key information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApiKey {
@@ -199,41 +330,7 @@ impl<S> SecurityMiddleware<S> {
             return;
         }
 
-        let headers = response.headers_mut();
-
-        // Content Security Policy
-        headers.insert(
-            header::CONTENT_SECURITY_POLICY,
-            header::HeaderValue::from_static(
-                "default-src 'self'; script-src 'self'; object-src 'none'; style-src 'self' 'unsafe-inline'; img-src 'self' data:; font-src 'self'; connect-src 'self'; media-src 'self';",
-            ),
-        );
-
-        // X-Content-Type-Options
-        headers.insert(header::HeaderName::from_static("x-content-type-options"), header::HeaderValue::from_static("nosniff"));
-
-        // X-Frame-Options
-        headers.insert(header::HeaderName::from_static("x-frame-options"), header::HeaderValue::from_static("DENY"));
-
-        // X-XSS-Protection
-        headers.insert(header::HeaderName::from_static("x-xss-protection"), header::HeaderValue::from_static("1; mode=block"));
-
-        // Strict-Transport-Security
-        headers.insert(
-            header::HeaderName::from_static("strict-transport-security"),
-            header::HeaderValue::from_static("max-age=31536000; includeSubDomains"),
-        );
-
-        // Referrer-Policy
-        headers.insert(header::HeaderName::from_static("referrer-policy"), header::HeaderValue::from_static("strict-origin-when-cross-origin"));
-
-        // Permissions-Policy
-        headers.insert(
-            header::HeaderName::from_static("permissions-policy"),
-            header::HeaderValue::from_static(
-                "geolocation=(), midi=(), notifications=(), push=(), sync-xhr=(), microphone=(), camera=(), magnetometer=(), gyroscope=(), speaker=(), vibrate=(), fullscreen=(), payment=()",
-            ),
-        );
+        SecurityHeaders::default().apply(response.headers_mut());
     }
 
     /// Check DDoS protection
@@ -803,4 +900,43 @@ mod tests {
         // This should fail
         assert!(middleware.check_request_size(&req).is_err());
     }
+
+    #[test]
+    fn test_security_headers() {
+        let mut headers = header::HeaderMap::new();
+        headers.insert(header::CONTENT_SECURITY_POLICY, header::HeaderValue::from_static("default-src 'none'"));
+        SecurityHeaders::new(&SecurityHeadersConfig {
+            hsts_max_age_secs: 600,
+            hsts_include_subdomains: false,
+            frame_options: "sameorigin".to_string(),
+            ..SecurityHeadersConfig::default()
+        })
+        .unwrap()
+        .apply(&mut headers);
+
+        assert_eq!(headers[header::STRICT_TRANSPORT_SECURITY], "max-age=600");
+        assert_eq!(headers[header::X_FRAME_OPTIONS], "SAMEORIGIN");
+        assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(headers[header::CONTENT_SECURITY_POLICY], "default-src 'none'");
+
+        let mut headers = header::HeaderMap::new();
+        SecurityHeaders::new(&SecurityHeadersConfig {
+            hsts_max_age_secs: 0,
+            content_security_policy: String::new(),
+            ..SecurityHeadersConfig::default()
+        })
+        .unwrap()
+        .apply(&mut headers);
+        assert!(!headers.contains_key(header::STRICT_TRANSPORT_SECURITY));
+        assert!(!headers.contains_key(header::CONTENT_SECURITY_POLICY));
+
+        let invalid = SecurityHeadersConfig {
+            frame_options: "ALLOW-FROM https://a.io".to_string(),
+            ..SecurityHeadersConfig::default()
+        };
+        assert_eq!(
+            SecurityHeaders::new(&invalid).unwrap_err(),
+            SecurityHeadersError::InvalidFrameOptions("ALLOW-FROM https://a.io".to_string())
+        );
+    }
 }
@@ -16,16 +16,26 @@
 
 //! HTTP server implementation using Hyper
 
+use crate::api_keys::ApiKeyStore;
+use crate::audit::AuditLogger;
 use crate::auth::AuthService;
 use crate::config::Config;
+use crate::cors::CorsPolicy;
 use crate::db::DatabaseClient;
 use crate::error::{ApiError, ApiResult};
+use crate::handlers::abi_validation::AbiValidationConfig;
 use crate::middleware::VersioningMiddleware;
+use crate::rate_limiting::{PriorityRateLimitConfig, PriorityRateLimiter};
 use crate::router::Router;
-use crate::security::{SecurityConfig, SecurityLayer};
-use crate::versioning::{CompatibilityChecker, DeprecationManager, SchemaEvolutionManager, VersionRegistry};
+use crate::security::{SecurityConfig, SecurityHeaders, SecurityLayer};
+use crate::shutdown::{Shutdown, termination_signal};
+use crate::telemetry::RequestTrace;
+use crate::versioning::{ApiVersions, CompatibilityChecker, DeprecationManager, SchemaEvolutionManager, VersionRegistry};
 use crate::vm::VmClient;
+use dotvm_common::log_capture::LogEvents;
+use http_body_util::BodyExt;
 use hyper::body::Incoming;
+use hyper::header::ORIGIN;
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
 use hyper::{Request, Response};
@@ -33,10 +43,18 @@ use hyper_util::rt::TokioIo;
 use std::convert::Infallible;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpListener;
 use tokio::sync::Mutex;
+use tokio::task::JoinSet;
 use tower::ServiceBuilder;
-use tracing::{error, info};
+use tracing::{Instrument, error, info, warn};
+
+/// How often the rate limit config file is checked for changes
+const RATE_LIMIT_RELOAD_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long queued audit records get to be written once connections have drained
+const AUDIT_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// API server using Hyper
 pub struct ApiServer {
@@ -47,6 +65,14 @@ pub struct ApiServer {
     db_client: DatabaseClient,
     vm_client: VmClient,
     versioning_middleware: Arc<VersioningMiddleware>,
+    rate_limiter: Arc<PriorityRateLimiter>,
+    audit_logger: Option<Arc<AuditLogger>>,
+    api_keys: Option<Arc<ApiKeyStore>>,
+    cors: Arc<CorsPolicy>,
+    security_headers: Arc<SecurityHeaders>,
+    shutdown: Shutdown,
+    /// Captured log events to forward to the runtime's centralized log store
+    log_events: Option<LogEvents>,
 }
 
 impl ApiServer {
@@ -57,14 +83,25 @@ impl ApiServer {
             message: format!("Invalid bind address: {}", e),
         })?;
 
+        // Refuse to start with browser-facing policies that cannot be enforced
+        let cors = Arc::new(CorsPolicy::new(&config.cors).map_err(|e| ApiError::BadRequest {
+            message: format!("Invalid CORS configuration: {}", e),
+        })?);
+        let security_headers = Arc::new(SecurityHeaders::new(&config.security_headers).map_err(|e| ApiError::BadRequest {
+            message: format!("Invalid security headers configuration: {}", e),
+        })?);
+        let api_versions = Arc::new(ApiVersions::new(&config.api_versions).map_err(|e| ApiError::BadRequest {
+            message: format!("Invalid API version configuration: {}", e),
+        })?);
+
         // Create authentication service
         let auth_service = Arc::new(Mutex::new(AuthService::new(&config.jwt_secret)));
 
         // Create database client
-        let db_client = DatabaseClient::new(&config.db_service_address)?;
+        let db_client = DatabaseClient::new(&config.db_service_address)?.with_transactions(config.transactions.clone());
 
         // Create VM client
-        let vm_client = VmClient::new(&config.vm_service_address).await?;
+        let vm_client = VmClient::with_pool_config(&config.vm_service_address, config.vm_pool.clone()).await?;
 
         // Initialize versioning components
         let version_registry = VersionRegistry::new();
@@ -75,8 +112,44 @@ impl ApiServer {
         // Create versioning middleware
         let versioning_middleware = Arc::new(VersioningMiddleware::new(version_registry, compatibility_checker, deprecation_manager, schema_manager));
 
+        // Create rate limiter, preferring the config file when one is present
+        let rate_limit_config = match &config.rate_limit_config_path {
+            Some(path) if path.exists() => PriorityRateLimitConfig::from_file(path).unwrap_or_else(|e| {
+                warn!("Invalid rate limit config {}, using environment: {}", path.display(), e);
+                config.rate_limit.clone()
+            }),
+            _ => config.rate_limit.clone(),
+        };
+        let rate_limiter = Arc::new(PriorityRateLimiter::new(rate_limit_config));
+
+        let shutdown = Shutdown::new();
+
+        let audit_logger = config.audit.enabled.then(|| Arc::new(AuditLogger::new(config.audit.clone(), db_client.clone())));
+        let api_keys = config.api_keys.enabled.then(|| Arc::new(ApiKeyStore::new(config.api_keys.clone(), db_client.clone())));
+
         // Create router
-        let router = Arc::new(Router::new(auth_service.clone(), db_client.clone(), vm_client.clone()).await?);
+        let mut router = Router::new(auth_service.clone(), db_client.clone(), vm_client.clone(), shutdown.clone())
+            .await?
+            .with_rate_limiter(rate_limiter.clone())
+            .with_docs(config.openapi_enabled, config.openapi_path.clone())
+            .with_api_versions(api_versions)
+            .with_abi_validation(AbiValidationConfig {
+                strict: config.abi_strict_validation,
+                cache_ttl: Duration::from_secs(config.abi_cache_ttl_secs),
+            })
+            .with_response_cache(config.response_cache.clone())
+            .with_websocket_multiplexing(config.websocket_multiplex.clone())
+            .with_deploy_uploads(config.deploy_uploads.clone())
+            .with_admission_control(config.admission.clone())
+            .with_readiness(config.readiness.clone())
+            .with_compat_check(bind_address);
+        if let Some(audit_logger) = &audit_logger {
+            router = router.with_audit_logger(audit_logger.clone());
+        }
+        if let Some(api_keys) = &api_keys {
+            router = router.with_api_keys(api_keys.clone());
+        }
+        let router = Arc::new(router);
 
         info!("API server created successfully with versioning support");
 
@@ -88,16 +161,39 @@ impl ApiServer {
             db_client,
             vm_client,
             versioning_middleware,
+            rate_limiter,
+            audit_logger,
+            api_keys,
+            cors,
+            security_headers,
+            shutdown,
+            log_events: None,
         })
     }
 
+    /// Forward `events`, captured from this gateway's tracing, to the runtime's centralized
+    /// log store while the server runs
+    pub fn with_log_forwarding(mut self, events: LogEvents) -> Self {
+        self.log_events = Some(events);
+        self
+    }
+
     /// Get the bind address
     pub fn bind_address(&self) -> SocketAddr {
         self.bind_address
     }
 
-    /// Start the server
-    pub async fn run(self) -> ApiResult<()> {
+    /// Handle that stops the server when triggered, as SIGTERM/SIGINT do
+    pub fn shutdown_handle(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    /// Start the server and serve until SIGTERM/SIGINT or the shutdown handle is triggered
+    ///
+    /// On shutdown the listener is closed and readiness starts failing right away,
+    /// in-flight requests get `shutdown_grace_period_secs` to finish, and WebSocket and
+    /// SSE streams are closed. Connections still open after the grace period are aborted.
+    pub async fn run(mut self) -> ApiResult<()> {
         // Create TCP listener
         let listener = TcpListener::bind(self.bind_address).await.map_err(|e| ApiError::IoError(e))?;
 
@@ -135,43 +231,165 @@ impl ApiServer {
         // Create security layer
         let security_layer = SecurityLayer::new(security_config, self.auth_service.clone());
 
-        // Accept connections
+        // Drop cached ABIs when dots are redeployed elsewhere
+        let invalidation_listener = self.router.abi_cache().spawn_invalidation_listener(self.vm_client.clone());
+        self.shutdown.abort_on_shutdown(invalidation_listener);
+
+        // Drop cached responses when dots are redeployed or change state
+        if self.router.response_cache().config().enabled {
+            let invalidation_listener = self.router.response_cache().spawn_invalidation_listener(self.vm_client.clone());
+            self.shutdown.abort_on_shutdown(invalidation_listener);
+        }
+
+        // Probe pooled VM channels and re-dial broken ones
+        self.shutdown.abort_on_shutdown(self.vm_client.spawn_health_checker());
+
+        // Ship the gateway's logs to the runtime, recorded under the gateway's bind address
+        if let Some(log_events) = self.log_events.take() {
+            self.shutdown.abort_on_shutdown(self.vm_client.spawn_log_forwarder(log_events, self.bind_address.to_string()));
+        }
+
+        // Abort transactions left open past their lifetime, releasing their locks
+        self.shutdown.abort_on_shutdown(self.db_client.transactions().spawn_reaper());
+
+        // Write audit records off the request path; the writer is flushed after connections drain
+        let audit_writer = self.audit_logger.as_ref().map(|audit_logger| audit_logger.spawn_writer());
+
+        // Record when API keys were last used in batches rather than on every request
+        if let Some(api_keys) = &self.api_keys {
+            self.shutdown.abort_on_shutdown(api_keys.spawn_last_used_flusher());
+        }
+
+        // Pick up rate limit changes without a restart
+        if let Some(path) = &self.config.rate_limit_config_path {
+            let watcher = self.rate_limiter.watch_config_file(path.clone(), RATE_LIMIT_RELOAD_INTERVAL);
+            self.shutdown.abort_on_shutdown(watcher);
+        }
+
+        let signal_shutdown = self.shutdown.clone();
+        tokio::spawn(async move {
+            termination_signal().await;
+            signal_shutdown.trigger();
+        });
+
+        // Accept connections until shutdown, reaping finished connection tasks as we go
+        let mut connections = JoinSet::new();
         loop {
-            let (stream, remote_addr) = match listener.accept().await {
-                Ok(conn) => conn,
-                Err(e) => {
-                    error!("Failed to accept connection: {}", e);
-                    continue;
-                }
+            let (stream, remote_addr) = tokio::select! {
+                _ = self.shutdown.triggered() => break,
+                Some(_) = connections.join_next(), if !connections.is_empty() => continue,
+                accepted = listener.accept() => match accepted {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        error!("Failed to accept connection: {}", e);
+                        continue;
+                    }
+                },
             };
 
             let io = TokioIo::new(stream);
             let router = self.router.clone();
+            let rate_limiter = self.rate_limiter.clone();
+            let api_keys = self.api_keys.clone();
+            let cors = self.cors.clone();
+            let security_headers = self.security_headers.clone();
+            let shutdown = self.shutdown.clone();
             //let security_layer = security_layer.clone();
 
             // Spawn a task to handle the connection
-            tokio::task::spawn(async move {
+            connections.spawn(async move {
                 // Create service with middleware
                 let service = ServiceBuilder::new()
                     //.layer(security_layer)
                     .service(service_fn(move |req: Request<Incoming>| {
                         let router = router.clone();
+                        let rate_limiter = rate_limiter.clone();
+                        let api_keys = api_keys.clone();
+                        let cors = cors.clone();
+                        let security_headers = security_headers.clone();
                         async move {
-                            match router.route(req).await {
-                                Ok(response) => Ok::<_, Infallible>(response),
+                            let origin = req.headers().get(ORIGIN).cloned();
+                            let api_key = req.headers().get("x-api-key").and_then(|v| v.to_str().ok()).map(str::to_string);
+                            // Keys the store does not accept are limited as anonymous traffic
+                            let authenticated = match (&api_keys, &api_key) {
+                                (Some(store), Some(secret)) => store.authenticate(secret).await.is_ok(),
+                                _ => false,
+                            };
+                            let decision = rate_limiter.check(api_key.as_deref(), authenticated, &remote_addr.ip().to_string()).await;
+                            if !decision.allowed {
+                                let mut response = Response::from(decision.to_error()).map(BodyExt::boxed_unsync);
+                                decision.apply_headers(&mut response);
+                                cors.apply(origin.as_ref(), response.headers_mut());
+                                security_headers.apply(response.headers_mut());
+                                return Ok::<_, Infallible>(response);
+                            }
+
+                            // Everything done for the request, including gRPC calls, joins its trace
+                            let trace = RequestTrace::from_headers(req.headers());
+                            let span = trace.span(req.method().as_str(), req.uri().path());
+
+                            // Preflights are answered and cross-origin WebSocket upgrades vetted before routing
+                            let routed = match cors.intercept(&req) {
+                                Some(result) => result.map(|response| response.map(BodyExt::boxed_unsync)),
+                                None => trace.clone().scope(router.route(req)).instrument(span.clone()).await,
+                            };
+
+                            let mut response = match routed {
+                                Ok(response) => response,
                                 Err(e) => {
-                                    error!("Request failed: {}", e);
-                                    Ok(Response::from(e))
+                                    span.in_scope(|| error!("Request failed: {}", e));
+                                    Response::from(e).map(BodyExt::boxed_unsync)
                                 }
-                            }
+                            };
+                            decision.apply_headers(&mut response);
+                            trace.apply_headers(response.headers_mut());
+                            cors.apply(origin.as_ref(), response.headers_mut());
+                            security_headers.apply(response.headers_mut());
+                            Ok(response)
                         }
                     }));
 
-                // Serve the connection
-                if let Err(err) = http1::Builder::new().serve_connection(io, service).await {
+                // Serve the connection, letting an in-flight request finish on shutdown
+                let connection = http1::Builder::new().serve_connection(io, service);
+                tokio::pin!(connection);
+                let result = tokio::select! {
+                    result = connection.as_mut() => result,
+                    _ = shutdown.triggered() => {
+                        connection.as_mut().graceful_shutdown();
+                        connection.await
+                    }
+                };
+                if let Err(err) = result {
                     error!("Error serving connection from {}: {}", remote_addr, err);
                 }
             });
         }
+
+        // Stop accepting before draining so new clients are refused rather than left waiting
+        drop(listener);
+
+        let grace_period = Duration::from_secs(self.config.shutdown_grace_period_secs);
+        info!("Shutting down, draining {} connections for up to {:?}", connections.len(), grace_period);
+        let drained = tokio::time::timeout(grace_period, async {
+            while connections.join_next().await.is_some() {}
+        })
+        .await;
+        if drained.is_err() {
+            warn!("Grace period elapsed with {} connections still open, aborting them", connections.len());
+            connections.shutdown().await;
+        }
+
+        if let (Some(audit_logger), Some(audit_writer)) = (&self.audit_logger, audit_writer) {
+            audit_logger.close();
+            if tokio::time::timeout(AUDIT_FLUSH_TIMEOUT, audit_writer).await.is_err() {
+                warn!("Audit log not flushed within {:?}, remaining records are lost", AUDIT_FLUSH_TIMEOUT);
+            }
+        }
+        if let Some(api_keys) = &self.api_keys {
+            api_keys.flush_last_used().await;
+        }
+
+        info!("API server stopped");
+        Ok(())
     }
 }
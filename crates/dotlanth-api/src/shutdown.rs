@@ -0,0 +1,141 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Graceful shutdown coordination
+//!
+//! A [`Shutdown`] handle is cloned into everything that has to wind down when the
+//! server stops: the accept loop, the readiness endpoint, long-lived WebSocket and
+//! SSE streams, and background tasks.
+
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Shared shutdown flag that can be awaited
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl Shutdown {
+    /// Create a handle that has not been triggered
+    pub fn new() -> Self {
+        let (sender, _) = watch::channel(false);
+        Self { sender: Arc::new(sender) }
+    }
+
+    /// Start shutting down; every clone observes it
+    pub fn trigger(&self) {
+        self.sender.send_replace(true);
+    }
+
+    /// Whether shutdown has started
+    pub fn is_triggered(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    /// Resolve once shutdown has started
+    pub async fn triggered(&self) {
+        let mut receiver = self.sender.subscribe();
+        // The sender lives as long as `self`, so waiting cannot fail
+        let _ = receiver.wait_for(|triggered| *triggered).await;
+    }
+
+    /// Abort a background task once shutdown starts
+    pub fn abort_on_shutdown<T: Send + 'static>(&self, task: JoinHandle<T>) {
+        let abort = task.abort_handle();
+        let shutdown = self.clone();
+        tokio::spawn(async move {
+            shutdown.triggered().await;
+            abort.abort();
+        });
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Resolve when the process receives SIGTERM or SIGINT
+pub async fn termination_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => tokio::select! {
+                _ = sigterm.recv() => info!("Received SIGTERM"),
+                _ = tokio::signal::ctrl_c() => info!("Received SIGINT"),
+            },
+            Err(e) => {
+                warn!("Failed to install SIGTERM handler, only SIGINT will stop the server: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+                info!("Received SIGINT");
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        info!("Received Ctrl-C");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_trigger_is_seen_by_clones() {
+        let shutdown = Shutdown::new();
+        let clone = shutdown.clone();
+        assert!(!clone.is_triggered());
+
+        let waiter = tokio::spawn(async move { clone.triggered().await });
+        shutdown.trigger();
+        tokio::time::timeout(Duration::from_secs(1), waiter).await.unwrap().unwrap();
+        assert!(shutdown.is_triggered());
+    }
+
+    #[tokio::test]
+    async fn test_triggered_resolves_after_the_fact() {
+        let shutdown = Shutdown::new();
+        shutdown.trigger();
+        tokio::time::timeout(Duration::from_secs(1), shutdown.triggered()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_abort_on_shutdown() {
+        let shutdown = Shutdown::new();
+        let task = tokio::spawn(std::future::pending::<()>());
+        let abort = task.abort_handle();
+        shutdown.abort_on_shutdown(task);
+
+        shutdown.trigger();
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while !abort.is_finished() {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+}
@@ -0,0 +1,138 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Read snapshots opened through the REST API
+//!
+//! `POST /api/v1/snapshots` pins a consistent snapshot of every collection and
+//! returns a token for it. Document reads that pass the token in the `snapshot`
+//! query parameter, whether reading one document, listing a collection or searching
+//! it, see the documents as they were when the snapshot was taken, so a report
+//! spread over several requests and collections reads a single point in time.
+//! `DELETE /api/v1/snapshots/{token}` releases the snapshot.
+//!
+//! A snapshot belongs to the token subject that took it; its token is unknown
+//! (404) to anyone else, and to everyone once it has been released. DotDB keeps
+//! the history a snapshot reads for as long as it is held, so one held past its
+//! maximum age is released by force and reads through it fail with 404.
+
+use crate::error::{ApiError, ApiResult};
+use dashmap::DashMap;
+use dotdb_core::document::ReadSnapshot;
+use std::sync::Arc;
+use tracing::{debug, info};
+use uuid::Uuid;
+
+/// Query parameter document reads carry a snapshot token in
+pub const SNAPSHOT_PARAM: &str = "snapshot";
+
+/// An open snapshot and who may read through it
+struct OpenSnapshot {
+    owner: String,
+    snapshot: Arc<ReadSnapshot>,
+}
+
+/// Snapshots taken through the REST API and not yet released
+#[derive(Default)]
+pub struct OpenSnapshots {
+    open: DashMap<Uuid, OpenSnapshot>,
+}
+
+impl OpenSnapshots {
+    /// Create an empty set of snapshots
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of open snapshots
+    pub fn len(&self) -> usize {
+        self.open.len()
+    }
+
+    /// Whether no snapshot is open
+    pub fn is_empty(&self) -> bool {
+        self.open.is_empty()
+    }
+
+    /// Keep `snapshot` open for `owner`, returning its token
+    pub fn insert(&self, owner: &str, snapshot: ReadSnapshot) -> Uuid {
+        self.sweep_released();
+
+        let id = Uuid::new_v4();
+        self.open.insert(
+            id,
+            OpenSnapshot {
+                owner: owner.to_string(),
+                snapshot: Arc::new(snapshot),
+            },
+        );
+        debug!("Opened snapshot {} for {}", id, owner);
+        id
+    }
+
+    /// The snapshot `id` of `owner`, to read through
+    pub fn get(&self, id: &str, owner: &str) -> ApiResult<Arc<ReadSnapshot>> {
+        let parsed = parse(id)?;
+        let snapshot = match self.open.get(&parsed) {
+            Some(open) if open.owner == owner => open.snapshot.clone(),
+            _ => return Err(unknown(id)),
+        };
+        if !snapshot.is_active() {
+            self.open.remove(&parsed);
+            return Err(released(parsed));
+        }
+        Ok(snapshot)
+    }
+
+    /// Release the snapshot `id` of `owner`
+    ///
+    /// Reads already running through the snapshot finish against it first.
+    pub fn release(&self, id: &str, owner: &str) -> ApiResult<()> {
+        let parsed = parse(id)?;
+        match self.open.remove_if(&parsed, |_, open| open.owner == owner) {
+            Some(_) => Ok(()),
+            None => Err(unknown(id)),
+        }
+    }
+
+    /// Stop tracking snapshots released by force, returning how many there were
+    pub fn sweep_released(&self) -> usize {
+        let before = self.open.len();
+        self.open.retain(|id, open| {
+            let active = open.snapshot.is_active();
+            if !active {
+                info!("Dropping snapshot {}: held past its maximum age", id);
+            }
+            active
+        });
+        before.saturating_sub(self.open.len())
+    }
+}
+
+fn parse(id: &str) -> ApiResult<Uuid> {
+    Uuid::parse_str(id).map_err(|_| unknown(id))
+}
+
+fn released(id: Uuid) -> ApiError {
+    ApiError::NotFound {
+        message: format!("Snapshot {} was held past its maximum age and released", id),
+    }
+}
+
+fn unknown(id: &str) -> ApiError {
+    ApiError::NotFound {
+        message: format!("Snapshot not found: {}", id),
+    }
+}
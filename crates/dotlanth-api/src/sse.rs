@@ -0,0 +1,257 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Server-sent events (`text/event-stream`) support
+//!
+//! Bridges gRPC event streams to SSE frames with event ids for resumption and
+//! periodic heartbeat comments to keep idle connections open through proxies.
+//! Streams end with a `shutdown` event when the server stops, so clients reconnect
+//! with `Last-Event-ID` instead of holding the connection through the grace period.
+
+use crate::error::ApiResult;
+use crate::models::DotEvent;
+use crate::router::RouterBody;
+use crate::shutdown::Shutdown;
+use futures::StreamExt;
+use futures::stream::BoxStream;
+use http_body_util::{BodyExt, StreamBody};
+use hyper::body::{Bytes, Frame};
+use std::convert::Infallible;
+use std::time::Duration;
+use tokio::time::{Interval, MissedTickBehavior, interval_at};
+use tracing::{debug, warn};
+
+/// Interval between heartbeat comments
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Reconnection delay advertised to clients
+pub const RETRY_MILLIS: u64 = 3000;
+
+/// A single server-sent event frame
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    /// Event id, echoed back by clients in `Last-Event-ID`
+    pub id: Option<String>,
+
+    /// Event name
+    pub event: Option<String>,
+
+    /// Event payload
+    pub data: String,
+}
+
+impl SseEvent {
+    /// Build an SSE frame from a dot event
+    pub fn from_dot_event(event: &DotEvent) -> ApiResult<Self> {
+        Ok(Self {
+            id: Some(event.event_id.clone()),
+            event: Some(event.event_type.clone()),
+            data: serde_json::to_string(event)?,
+        })
+    }
+
+    /// Encode the event in wire format
+    pub fn encode(&self) -> Bytes {
+        let mut out = String::new();
+        if let Some(id) = &self.id {
+            out.push_str("id: ");
+            out.push_str(&sanitize_field(id));
+            out.push('\n');
+        }
+        if let Some(event) = &self.event {
+            out.push_str("event: ");
+            out.push_str(&sanitize_field(event));
+            out.push('\n');
+        }
+        // Multi-line payloads are split across several `data:` fields
+        for line in self.data.split('\n') {
+            out.push_str("data: ");
+            out.push_str(line.trim_end_matches('\r'));
+            out.push('\n');
+        }
+        out.push('\n');
+        Bytes::from(out)
+    }
+}
+
+/// Heartbeat comment frame
+pub fn heartbeat() -> Bytes {
+    Bytes::from_static(b": heartbeat\n\n")
+}
+
+/// Retry advice sent as the first frame of every stream
+pub fn retry_frame() -> Bytes {
+    Bytes::from(format!("retry: {}\n\n", RETRY_MILLIS))
+}
+
+/// Strip line breaks from single-line fields so they cannot inject extra fields
+fn sanitize_field(value: &str) -> String {
+    value.chars().filter(|c| *c != '\n' && *c != '\r').collect()
+}
+
+/// Logs when the HTTP client goes away and the upstream stream is dropped
+struct StreamGuard {
+    dot_id: String,
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        debug!("SSE stream for dot {} closed, upstream gRPC stream released", self.dot_id);
+    }
+}
+
+/// State threaded through the body stream
+struct EventStreamState {
+    events: BoxStream<'static, ApiResult<DotEvent>>,
+    heartbeat: Interval,
+    started: bool,
+    finished: bool,
+    shutdown: Shutdown,
+    guard: StreamGuard,
+}
+
+/// Turn a stream of dot events into an SSE response body
+///
+/// The upstream stream is owned by the body, so when hyper drops the body on
+/// client disconnect the gRPC call is cancelled as well.
+pub fn dot_event_stream(dot_id: String, events: BoxStream<'static, ApiResult<DotEvent>>, heartbeat_interval: Duration, shutdown: Shutdown) -> RouterBody {
+    let start = tokio::time::Instant::now() + heartbeat_interval;
+    let mut heartbeat = interval_at(start, heartbeat_interval);
+    heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let state = EventStreamState {
+        events,
+        heartbeat,
+        started: false,
+        finished: false,
+        shutdown,
+        guard: StreamGuard { dot_id },
+    };
+
+    let frames = futures::stream::unfold(state, |mut state| async move {
+        if state.finished {
+            return None;
+        }
+        if !state.started {
+            state.started = true;
+            return Some((retry_frame(), state));
+        }
+
+        let frame = tokio::select! {
+            item = state.events.next() => match item {
+                Some(Ok(event)) => match SseEvent::from_dot_event(&event) {
+                    Ok(sse) => sse.encode(),
+                    Err(e) => {
+                        warn!("Failed to encode dot event {}: {}", event.event_id, e);
+                        heartbeat()
+                    }
+                },
+                Some(Err(e)) => {
+                    warn!("Upstream event stream for dot {} failed: {}", state.guard.dot_id, e);
+                    state.finished = true;
+                    SseEvent {
+                        id: None,
+                        event: Some("error".to_string()),
+                        data: serde_json::json!({ "message": e.to_string() }).to_string(),
+                    }
+                    .encode()
+                }
+                None => return None,
+            },
+            _ = state.heartbeat.tick() => heartbeat(),
+            _ = state.shutdown.triggered() => {
+                state.finished = true;
+                SseEvent {
+                    id: None,
+                    event: Some("shutdown".to_string()),
+                    data: serde_json::json!({ "message": "Server shutting down, reconnect to resume" }).to_string(),
+                }
+                .encode()
+            }
+        };
+
+        // Any outgoing frame keeps the connection alive, so push the next heartbeat back
+        state.heartbeat.reset();
+        Some((frame, state))
+    });
+
+    StreamBody::new(frames.map(|bytes| Ok::<_, Infallible>(Frame::data(bytes)))).boxed_unsync()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_event() {
+        let event = SseEvent {
+            id: Some("42".to_string()),
+            event: Some("state_changed".to_string()),
+            data: "{\"a\":1}".to_string(),
+        };
+        assert_eq!(event.encode(), Bytes::from("id: 42\nevent: state_changed\ndata: {\"a\":1}\n\n"));
+    }
+
+    #[test]
+    fn test_encode_multiline_data() {
+        let event = SseEvent {
+            id: None,
+            event: None,
+            data: "line1\r\nline2".to_string(),
+        };
+        assert_eq!(event.encode(), Bytes::from("data: line1\ndata: line2\n\n"));
+    }
+
+    #[test]
+    fn test_field_injection_is_stripped() {
+        let event = SseEvent {
+            id: Some("1\nevent: spoofed".to_string()),
+            event: None,
+            data: String::new(),
+        };
+        assert_eq!(event.encode(), Bytes::from("id: 1event: spoofed\ndata: \n\n"));
+    }
+
+    #[tokio::test]
+    async fn test_stream_ends_with_upstream() {
+        let event = DotEvent {
+            event_id: "e1".to_string(),
+            dot_id: "dot".to_string(),
+            event_type: "executed".to_string(),
+            data: serde_json::Value::Null,
+            metadata: Default::default(),
+        };
+        let events = futures::stream::iter(vec![Ok(event)]).boxed();
+        let body = dot_event_stream("dot".to_string(), events, HEARTBEAT_INTERVAL, Shutdown::new());
+        let bytes = body.collect().await.unwrap().to_bytes();
+        let text = String::from_utf8(bytes.to_vec()).unwrap();
+
+        assert!(text.starts_with("retry: 3000\n\n"));
+        assert!(text.contains("id: e1\nevent: executed\n"));
+    }
+
+    #[tokio::test]
+    async fn test_stream_ends_on_shutdown() {
+        let shutdown = Shutdown::new();
+        let events = futures::stream::pending::<ApiResult<DotEvent>>().boxed();
+        let body = dot_event_stream("dot".to_string(), events, HEARTBEAT_INTERVAL, shutdown.clone());
+        shutdown.trigger();
+
+        let bytes = tokio::time::timeout(Duration::from_secs(1), body.collect()).await.unwrap().unwrap().to_bytes();
+        let text = String::from_utf8(bytes.to_vec()).unwrap();
+        assert!(text.ends_with("event: shutdown\ndata: {\"message\":\"Server shutting down, reconnect to resume\"}\n\n"));
+    }
+}
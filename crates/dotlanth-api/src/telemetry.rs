@@ -0,0 +1,199 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Per-request trace context
+//!
+//! Every HTTP request is served with a [`RequestTrace`] in scope. gRPC calls to
+//! the runtime run the [`propagate_trace`] interceptor, which copies it into the
+//! outgoing metadata, so the runtime's spans join the same trace. Authenticated requests
+//! also have their [`RequestCaller`] in scope, which the interceptor forwards so
+//! the runtime can check the dot permissions of the caller.
+
+use dotvm_common::telemetry::{PRINCIPAL_HEADER, REQUEST_ID_HEADER, ROLES_HEADER, TRACEPARENT_HEADER, TraceContext};
+use hyper::header::{HeaderMap, HeaderValue};
+use std::future::Future;
+use tonic::metadata::MetadataValue;
+use tonic::{Request, Status};
+use tracing::{Span, info_span};
+
+tokio::task_local! {
+    static CURRENT: RequestTrace;
+    static CALLER: RequestCaller;
+}
+
+/// Trace context and request id of the HTTP request being served
+#[derive(Debug, Clone)]
+pub struct RequestTrace {
+    /// Context of the gateway's span for this request
+    pub context: TraceContext,
+    /// Context the caller sent in `traceparent`, if any
+    pub parent: Option<TraceContext>,
+    pub request_id: String,
+}
+
+impl RequestTrace {
+    /// Continue the caller's trace and request id, or start new ones
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+        let parent = header(TRACEPARENT_HEADER).and_then(TraceContext::parse);
+
+        Self {
+            context: parent.map_or_else(TraceContext::new_root, |parent| parent.child()),
+            parent,
+            request_id: header(REQUEST_ID_HEADER)
+                .filter(|id| !id.is_empty())
+                .map(str::to_string)
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+        }
+    }
+
+    /// The trace of the request being served by this task
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(Clone::clone).ok()
+    }
+
+    /// Root span of the request, searchable by its request and trace ids
+    pub fn span(&self, method: &str, path: &str) -> Span {
+        let span = info_span!(
+            "http_request",
+            method,
+            path,
+            request_id = %self.request_id,
+            trace_id = %self.context.trace_id_hex(),
+            span_id = %self.context.span_id_hex(),
+        );
+        if let Some(parent) = &self.parent {
+            parent.attach_as_parent(&span);
+        }
+        span
+    }
+
+    /// Run `future` with this trace as the current one
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        CURRENT.scope(self, future).await
+    }
+
+    /// Echo the trace context and request id back to the caller
+    pub fn apply_headers(&self, headers: &mut HeaderMap) {
+        if let Ok(value) = HeaderValue::from_str(&self.context.to_string()) {
+            headers.insert(TRACEPARENT_HEADER, value);
+        }
+        if let Ok(value) = HeaderValue::from_str(&self.request_id) {
+            headers.insert(REQUEST_ID_HEADER, value);
+        }
+    }
+}
+
+/// Principal and roles the request being served was authenticated with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestCaller {
+    pub principal: String,
+    pub roles: Vec<String>,
+}
+
+impl RequestCaller {
+    /// The caller of the request being served by this task, if it was authenticated
+    pub fn current() -> Option<Self> {
+        CALLER.try_with(Clone::clone).ok()
+    }
+
+    /// Run `future` with this caller as the current one
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        CALLER.scope(self, future).await
+    }
+}
+
+/// gRPC interceptor adding the current request's `traceparent`, request id and caller to outgoing metadata
+///
+/// When spans are exported the `traceparent` names the span making the call, otherwise the
+/// gateway's request span.
+pub fn propagate_trace(mut request: Request<()>) -> Result<Request<()>, Status> {
+    if let Some(caller) = RequestCaller::current() {
+        if let Ok(value) = MetadataValue::try_from(caller.principal.as_str()) {
+            request.metadata_mut().insert(PRINCIPAL_HEADER, value);
+        }
+        if let Ok(value) = MetadataValue::try_from(caller.roles.join(",")) {
+            request.metadata_mut().insert(ROLES_HEADER, value);
+        }
+    }
+    let Some(trace) = RequestTrace::current() else {
+        return Ok(request);
+    };
+
+    let context = TraceContext::from_span(&Span::current()).unwrap_or(trace.context);
+    if let Ok(value) = MetadataValue::try_from(context.to_string()) {
+        request.metadata_mut().insert(TRACEPARENT_HEADER, value);
+    }
+    if let Ok(value) = MetadataValue::try_from(trace.request_id.as_str()) {
+        request.metadata_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    Ok(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn test_continues_the_callers_trace() {
+        let mut headers = HeaderMap::new();
+        headers.insert(TRACEPARENT_HEADER, HeaderValue::from_static(TRACEPARENT));
+        headers.insert(REQUEST_ID_HEADER, HeaderValue::from_static("req-1"));
+
+        let trace = RequestTrace::from_headers(&headers);
+        assert_eq!(trace.context.trace_id_hex(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_ne!(trace.context.span_id_hex(), "00f067aa0ba902b7");
+        assert_eq!(trace.request_id, "req-1");
+
+        // Malformed context starts a new trace instead of failing the request
+        headers.insert(TRACEPARENT_HEADER, HeaderValue::from_static("garbage"));
+        headers.remove(REQUEST_ID_HEADER);
+        let trace = RequestTrace::from_headers(&headers);
+        assert!(trace.parent.is_none());
+        assert!(!trace.request_id.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_interceptor_propagates_the_current_trace() {
+        assert!(propagate_trace(Request::new(())).unwrap().metadata().is_empty());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(TRACEPARENT_HEADER, HeaderValue::from_static(TRACEPARENT));
+        let trace = RequestTrace::from_headers(&headers);
+        let expected = trace.clone();
+
+        let request = trace.scope(async { propagate_trace(Request::new(())).unwrap() }).await;
+        let metadata = request.metadata();
+        assert_eq!(metadata.get(TRACEPARENT_HEADER).unwrap(), expected.context.to_string().as_str());
+        assert_eq!(metadata.get(REQUEST_ID_HEADER).unwrap(), expected.request_id.as_str());
+        assert!(metadata.get(PRINCIPAL_HEADER).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_interceptor_forwards_the_authenticated_caller() {
+        let caller = RequestCaller {
+            principal: "alice".to_string(),
+            roles: vec!["admin".to_string(), "auditor".to_string()],
+        };
+
+        let request = caller.scope(async { propagate_trace(Request::new(())).unwrap() }).await;
+        let metadata = request.metadata();
+        assert_eq!(metadata.get(PRINCIPAL_HEADER).unwrap(), "alice");
+        assert_eq!(metadata.get(ROLES_HEADER).unwrap(), "admin,auditor");
+    }
+}
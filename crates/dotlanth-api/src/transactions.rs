@@ -0,0 +1,284 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Document transactions opened through the REST API
+//!
+//! `POST /api/v1/transactions` begins a transaction and returns its id. Document
+//! requests that carry the id in the `X-Transaction-Id` header run inside it:
+//! creating, reading, updating and deleting a single document all join, while
+//! listing and search keep reading committed documents.
+//! `POST /api/v1/transactions/{id}/commit` applies the transaction's writes and
+//! `DELETE /api/v1/transactions/{id}` discards them.
+//!
+//! A transaction belongs to the token subject that began it. Its id is unknown
+//! (404) to anyone else, and to everyone once it has finished. Transactions are
+//! held in memory and capped twice: one open longer than `max_lifetime` is aborted, releasing its
+//! locks, and one that runs more than `max_operations` document operations is
+//! aborted by the operation that goes over.
+//!
+//! Locks are never waited for, so transactions cannot deadlock: the one that would
+//! wait is aborted instead. A request that aborts its transaction fails with
+//! `409 Conflict` and a problem document carrying a `code` and whether running the
+//! whole transaction again may succeed (`retryable`):
+//!
+//! | `code`                | `retryable` | Cause                                                     |
+//! |-----------------------|-------------|-----------------------------------------------------------|
+//! | `lock_conflict`       | yes         | Another open transaction has the document locked          |
+//! | `write_conflict`      | yes         | The document changed after this transaction read it       |
+//! | `transaction_expired` | yes         | The transaction outlived `max_lifetime`                   |
+//! | `operation_limit`     | no          | The transaction ran more than `max_operations` operations |
+//!
+//! The transaction is gone after any of these. Other errors, such as a missing
+//! document or a schema violation, fail only the request and leave it open.
+
+use crate::error::{ApiError, ApiResult};
+use crate::grpc_pool::parse_env;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use dotdb_core::document::{AbortReason, DocumentTransaction};
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{debug, info};
+use uuid::Uuid;
+
+/// Header document requests carry a transaction id in to join it
+pub const TRANSACTION_HEADER: &str = "x-transaction-id";
+
+/// How often expired transactions are looked for
+const REAP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Transaction limits
+#[derive(Debug, Clone)]
+pub struct TransactionConfig {
+    /// Time a transaction may stay open before it is aborted
+    pub max_lifetime: Duration,
+
+    /// Document operations a transaction may run
+    pub max_operations: usize,
+}
+
+impl Default for TransactionConfig {
+    fn default() -> Self {
+        Self {
+            max_lifetime: Duration::from_secs(30),
+            max_operations: 1000,
+        }
+    }
+}
+
+impl TransactionConfig {
+    /// Load settings from `DOTLANTH_TXN_MAX_LIFETIME_SECS` and `DOTLANTH_TXN_MAX_OPERATIONS`
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Some(secs) = parse_env::<u64>("DOTLANTH_TXN_MAX_LIFETIME_SECS").filter(|&secs| secs > 0) {
+            config.max_lifetime = Duration::from_secs(secs);
+        }
+        if let Some(max_operations) = parse_env::<usize>("DOTLANTH_TXN_MAX_OPERATIONS").filter(|&max_operations| max_operations > 0) {
+            config.max_operations = max_operations;
+        }
+
+        config
+    }
+}
+
+/// An open transaction and who may use it
+struct OpenTransaction {
+    owner: String,
+    expires_at: Instant,
+    /// `None` once the transaction has been taken to commit or abort
+    state: Mutex<Option<TransactionState>>,
+}
+
+struct TransactionState {
+    txn: DocumentTransaction,
+    operations: usize,
+}
+
+impl OpenTransaction {
+    fn expired(&self) -> bool {
+        Instant::now() >= self.expires_at
+    }
+}
+
+/// Transactions begun through the REST API and not yet finished
+pub struct OpenTransactions {
+    config: TransactionConfig,
+    open: DashMap<Uuid, Arc<OpenTransaction>>,
+}
+
+impl OpenTransactions {
+    /// Create an empty set of transactions with the given limits
+    pub fn new(config: TransactionConfig) -> Self {
+        Self { config, open: DashMap::new() }
+    }
+
+    /// Limits applied to the transactions
+    pub fn config(&self) -> &TransactionConfig {
+        &self.config
+    }
+
+    /// Number of open transactions
+    pub fn len(&self) -> usize {
+        self.open.len()
+    }
+
+    /// Whether no transaction is open
+    pub fn is_empty(&self) -> bool {
+        self.open.is_empty()
+    }
+
+    /// Keep `txn` open for `owner`, returning its id and when it expires
+    pub fn insert(&self, owner: &str, txn: DocumentTransaction) -> (Uuid, DateTime<Utc>) {
+        self.sweep_expired();
+
+        let id = Uuid::new_v4();
+        let lifetime = self.config.max_lifetime;
+        self.open.insert(
+            id,
+            Arc::new(OpenTransaction {
+                owner: owner.to_string(),
+                expires_at: Instant::now() + lifetime,
+                state: Mutex::new(Some(TransactionState { txn, operations: 0 })),
+            }),
+        );
+        debug!("Opened transaction {} for {}", id, owner);
+
+        let expires_at = Utc::now() + chrono::Duration::from_std(lifetime).unwrap_or(chrono::Duration::MAX);
+        (id, expires_at)
+    }
+
+    /// Run one document operation in the transaction `id` of `owner`
+    ///
+    /// The transaction is dropped, rolling it back, if the operation aborts it,
+    /// it has expired or it runs one operation too many.
+    pub fn run<T>(&self, id: &str, owner: &str, operation: impl FnOnce(&mut DocumentTransaction) -> ApiResult<T>) -> ApiResult<T> {
+        let (id, open) = self.lookup(id, owner)?;
+        if open.expired() {
+            self.open.remove(&id);
+            return Err(expired(id));
+        }
+
+        let mut state = open.state.lock();
+        let Some(current) = state.as_mut() else {
+            return Err(unknown(id));
+        };
+        current.operations += 1;
+        if current.operations > self.config.max_operations {
+            state.take();
+            self.open.remove(&id);
+            return Err(ApiError::TransactionAborted {
+                message: format!("Transaction {} ran more than {} operations", id, self.config.max_operations),
+                code: "operation_limit",
+                retryable: false,
+            });
+        }
+
+        let result = operation(&mut current.txn);
+        if matches!(result, Err(ApiError::TransactionAborted { .. })) {
+            state.take();
+            self.open.remove(&id);
+        }
+        result
+    }
+
+    /// Stop tracking the transaction `id` of `owner` and hand it over to be committed
+    pub fn take(&self, id: &str, owner: &str) -> ApiResult<DocumentTransaction> {
+        let (id, open) = self.lookup(id, owner)?;
+        self.open.remove(&id);
+        let state = open.state.lock().take().ok_or_else(|| unknown(id))?;
+        if open.expired() {
+            return Err(expired(id));
+        }
+        Ok(state.txn)
+    }
+
+    /// Roll back the transaction `id` of `owner`
+    ///
+    /// Aborting a transaction that has expired succeeds, since it was aborted anyway.
+    pub fn abort(&self, id: &str, owner: &str) -> ApiResult<()> {
+        let (id, open) = self.lookup(id, owner)?;
+        self.open.remove(&id);
+        let state = open.state.lock().take().ok_or_else(|| unknown(id))?;
+        state.txn.rollback();
+        Ok(())
+    }
+
+    /// Roll back every expired transaction, returning how many there were
+    pub fn sweep_expired(&self) -> usize {
+        let before = self.open.len();
+        self.open.retain(|id, open| {
+            let expired = open.expired();
+            if expired {
+                info!("Aborting transaction {}: open longer than {:?}", id, self.config.max_lifetime);
+            }
+            !expired
+        });
+        before.saturating_sub(self.open.len())
+    }
+
+    /// Start aborting transactions as they expire
+    pub fn spawn_reaper(self: &Arc<Self>) -> JoinHandle<()> {
+        let transactions = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(REAP_INTERVAL.min(transactions.config.max_lifetime));
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                transactions.sweep_expired();
+            }
+        })
+    }
+
+    fn lookup(&self, id: &str, owner: &str) -> ApiResult<(Uuid, Arc<OpenTransaction>)> {
+        let parsed = Uuid::parse_str(id).map_err(|_| ApiError::NotFound {
+            message: format!("Transaction not found: {}", id),
+        })?;
+        match self.open.get(&parsed).map(|open| open.clone()) {
+            Some(open) if open.owner == owner => Ok((parsed, open)),
+            _ => Err(unknown(parsed)),
+        }
+    }
+}
+
+/// Map an abort reported by DotDB to the error clients retry on
+pub fn abort_error(reason: &AbortReason) -> ApiError {
+    let code = match reason {
+        AbortReason::LockConflict { .. } => "lock_conflict",
+        AbortReason::WriteConflict { .. } => "write_conflict",
+    };
+    ApiError::TransactionAborted {
+        message: reason.to_string(),
+        code,
+        retryable: true,
+    }
+}
+
+fn expired(id: Uuid) -> ApiError {
+    ApiError::TransactionAborted {
+        message: format!("Transaction {} expired and was aborted", id),
+        code: "transaction_expired",
+        retryable: true,
+    }
+}
+
+fn unknown(id: Uuid) -> ApiError {
+    ApiError::NotFound {
+        message: format!("Transaction not found: {}", id),
+    }
+}
@@ -0,0 +1,489 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Streaming multipart uploads of large dot deployments
+//!
+//! A multipart deployment carries a JSON `metadata` part followed by a `bytecode` part.
+//! The bytecode is never held whole by the gateway: it is read from the request body in
+//! fixed-size chunks and forwarded to the runtime as it arrives.
+
+use crate::error::{ApiError, ApiResult};
+use crate::grpc_pool::parse_env;
+use crate::models::{DeploymentState, DeploymentStatus};
+use chrono::Utc;
+use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
+use http_body_util::BodyExt;
+use hyper::HeaderMap;
+use hyper::body::{Body, Bytes};
+use hyper::header::CONTENT_TYPE;
+use ring::digest::{Context, SHA256};
+use std::fmt::Display;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Header naming a streamed deployment, so its progress can be polled while it uploads
+pub const UPLOAD_ID_HEADER: &str = "x-upload-id";
+
+/// Largest metadata part accepted ahead of the bytecode
+pub const MAX_METADATA_BYTES: usize = 64 * 1024;
+
+/// Longest headers accepted at the start of a part
+const MAX_PART_HEADER_BYTES: usize = 8 * 1024;
+
+/// Limits of streamed deployments
+#[derive(Debug, Clone)]
+pub struct UploadConfig {
+    /// Largest bytecode accepted; the upload is aborted as soon as it grows past this
+    pub max_bytes: u64,
+
+    /// Size of the chunks bytecode is forwarded to the runtime in
+    pub chunk_size: usize,
+
+    /// How long the status of a finished deployment can still be polled
+    pub status_retention: Duration,
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        Self {
+            max_bytes: 512 * 1024 * 1024,
+            chunk_size: 64 * 1024,
+            status_retention: Duration::from_secs(600),
+        }
+    }
+}
+
+impl UploadConfig {
+    /// Load settings from `DOTLANTH_MAX_DEPLOY_BYTES`, `DOTLANTH_DEPLOY_CHUNK_BYTES` and
+    /// `DOTLANTH_DEPLOY_STATUS_RETENTION_SECS`
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Some(max_bytes) = parse_env::<u64>("DOTLANTH_MAX_DEPLOY_BYTES").filter(|&max_bytes| max_bytes > 0) {
+            config.max_bytes = max_bytes;
+        }
+        if let Some(chunk_size) = parse_env::<usize>("DOTLANTH_DEPLOY_CHUNK_BYTES").filter(|&chunk_size| chunk_size > 0) {
+            config.chunk_size = chunk_size;
+        }
+        if let Some(secs) = parse_env::<u64>("DOTLANTH_DEPLOY_STATUS_RETENTION_SECS") {
+            config.status_retention = Duration::from_secs(secs);
+        }
+
+        config
+    }
+}
+
+/// Boundary of a `multipart/form-data` request, or `None` for any other content type
+pub fn multipart_boundary(headers: &HeaderMap) -> Option<String> {
+    let mime: mime::Mime = headers.get(CONTENT_TYPE)?.to_str().ok()?.parse().ok()?;
+    if mime.type_() != mime::MULTIPART || mime.subtype() != mime::FORM_DATA {
+        return None;
+    }
+    mime.get_param(mime::BOUNDARY).map(|boundary| boundary.to_string())
+}
+
+/// Upload ID chosen by the client in `X-Upload-Id`, or a fresh one when it did not pick any
+pub fn upload_id(headers: &HeaderMap) -> ApiResult<String> {
+    let Some(value) = headers.get(UPLOAD_ID_HEADER) else {
+        return Ok(Uuid::new_v4().to_string());
+    };
+    match value.to_str() {
+        Ok(id) if !id.is_empty() && id.len() <= 128 && id.bytes().all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.')) => Ok(id.to_string()),
+        _ => Err(ApiError::BadRequest {
+            message: "X-Upload-Id must be 1 to 128 letters, digits, '-', '_' or '.'".to_string(),
+        }),
+    }
+}
+
+/// Reader of a `multipart/form-data` body, part by part
+///
+/// Part contents are handed out in chunks as the body arrives, so no more than a chunk and
+/// one body frame are held in memory however large the part is.
+pub struct MultipartUpload<B> {
+    body: B,
+    /// `\r\n--boundary`, which ends every part
+    delimiter: Vec<u8>,
+    buffer: Vec<u8>,
+    /// Leading bytes of `buffer` known not to start a delimiter
+    scanned: usize,
+    chunk_size: usize,
+    in_part: bool,
+    finished: bool,
+    end_of_body: bool,
+    bytes_read: u64,
+    digest: Context,
+}
+
+impl<B> MultipartUpload<B>
+where
+    B: Body<Data = Bytes> + Unpin,
+    B::Error: Display,
+{
+    pub fn new(body: B, boundary: &str, chunk_size: usize) -> Self {
+        Self {
+            body,
+            delimiter: format!("\r\n--{}", boundary).into_bytes(),
+            // The first boundary may open the body without a line break before it
+            buffer: b"\r\n".to_vec(),
+            scanned: 0,
+            chunk_size: chunk_size.max(1),
+            in_part: false,
+            finished: false,
+            end_of_body: false,
+            bytes_read: 0,
+            digest: Context::new(&SHA256),
+        }
+    }
+
+    /// Start the next part, skipping what is left of the current one, and return its field
+    /// name; `None` once the closing boundary has been read
+    pub async fn next_part(&mut self) -> ApiResult<Option<String>> {
+        while self.next_chunk().await?.is_some() {}
+        if self.finished {
+            return Ok(None);
+        }
+
+        // Skip the preamble, or the rest of the part, up to the delimiter
+        loop {
+            if let Some(start) = self.find_delimiter() {
+                self.consume(start + self.delimiter.len());
+                break;
+            }
+            self.consume(self.scanned);
+            self.fill().await?;
+        }
+
+        while self.buffer.len() < 2 {
+            self.fill().await?;
+        }
+        if self.buffer.starts_with(b"--") {
+            // Read the epilogue so the body digest covers all of it
+            self.finished = true;
+            while !self.end_of_body {
+                self.buffer.clear();
+                let _ = self.fill().await;
+            }
+            self.buffer.clear();
+            return Ok(None);
+        }
+
+        let end = loop {
+            if let Some(end) = find(&self.buffer, b"\r\n\r\n") {
+                break end;
+            }
+            if self.buffer.len() > MAX_PART_HEADER_BYTES {
+                return Err(ApiError::BadRequest {
+                    message: "Multipart part headers are too long".to_string(),
+                });
+            }
+            self.fill().await?;
+        };
+        let name = part_name(&self.buffer[..end]);
+        self.consume(end + 4);
+        self.in_part = true;
+
+        name.map(Some).ok_or_else(|| ApiError::BadRequest {
+            message: "Multipart part has no field name".to_string(),
+        })
+    }
+
+    /// Next chunk of the current part, `chunk_size` bytes long except for the last one;
+    /// `None` at the end of the part
+    pub async fn next_chunk(&mut self) -> ApiResult<Option<Bytes>> {
+        if !self.in_part {
+            return Ok(None);
+        }
+
+        loop {
+            let delimiter = self.find_delimiter();
+            if delimiter == Some(0) {
+                self.in_part = false;
+                return Ok(None);
+            }
+
+            // Bytes before the delimiter, or that cannot start one, belong to the part
+            let available = delimiter.unwrap_or(self.scanned);
+            if delimiter.is_some() || available >= self.chunk_size {
+                let len = available.min(self.chunk_size);
+                let chunk = Bytes::copy_from_slice(&self.buffer[..len]);
+                self.consume(len);
+                return Ok(Some(chunk));
+            }
+            self.fill().await?;
+        }
+    }
+
+    /// Rest of the current part, refused once it grows past `limit` bytes
+    pub async fn read_part(&mut self, limit: usize) -> ApiResult<Vec<u8>> {
+        let mut part = Vec::new();
+        while let Some(chunk) = self.next_chunk().await? {
+            if part.len() + chunk.len() > limit {
+                return Err(ApiError::PayloadTooLarge {
+                    message: format!("Multipart part exceeds {} bytes", limit),
+                });
+            }
+            part.extend_from_slice(&chunk);
+        }
+        Ok(part)
+    }
+
+    /// Bytes of the request body read so far
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// SHA-256 of the request body read so far, all of it once the closing boundary is read
+    pub fn body_digest(&self) -> Vec<u8> {
+        self.digest.clone().finish().as_ref().to_vec()
+    }
+
+    /// Position of the next delimiter in the buffer, remembering how far it is known to be absent
+    fn find_delimiter(&mut self) -> Option<usize> {
+        match find(&self.buffer[self.scanned..], &self.delimiter) {
+            Some(offset) => Some(self.scanned + offset),
+            None => {
+                self.scanned = self.scanned.max(self.buffer.len().saturating_sub(self.delimiter.len() - 1));
+                None
+            }
+        }
+    }
+
+    fn consume(&mut self, len: usize) {
+        self.buffer.drain(..len);
+        self.scanned = self.scanned.saturating_sub(len);
+    }
+
+    /// Append the next frame of the body to the buffer
+    async fn fill(&mut self) -> ApiResult<()> {
+        while !self.end_of_body {
+            match self.body.frame().await {
+                Some(frame) => {
+                    let frame = frame.map_err(|e| ApiError::BadRequest {
+                        message: format!("Upload interrupted: {}", e),
+                    })?;
+                    if let Ok(data) = frame.into_data() {
+                        self.digest.update(&data);
+                        self.bytes_read += data.len() as u64;
+                        self.buffer.extend_from_slice(&data);
+                        return Ok(());
+                    }
+                }
+                None => self.end_of_body = true,
+            }
+        }
+        Err(ApiError::BadRequest {
+            message: "Upload ended before the closing multipart boundary".to_string(),
+        })
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    let mut start = 0;
+    while let Some(offset) = haystack[start..].iter().position(|&byte| byte == needle[0]) {
+        let candidate = start + offset;
+        if haystack.len() - candidate < needle.len() {
+            return None;
+        }
+        if haystack[candidate..].starts_with(needle) {
+            return Some(candidate);
+        }
+        start = candidate + 1;
+    }
+    None
+}
+
+/// Field name from the `Content-Disposition` header of a part
+fn part_name(headers: &[u8]) -> Option<String> {
+    let headers = std::str::from_utf8(headers).ok()?;
+    let disposition = headers.split("\r\n").find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim().eq_ignore_ascii_case("content-disposition").then_some(value)
+    })?;
+    disposition
+        .split(';')
+        .skip(1)
+        .find_map(|param| param.trim().strip_prefix("name="))
+        .map(|name| name.trim_matches('"').to_string())
+}
+
+/// Progress of streamed deployments, kept for a while after they finish so clients can
+/// see how they ended
+pub struct DeploymentTracker {
+    deployments: DashMap<String, DeploymentStatus>,
+    retention: Duration,
+}
+
+impl DeploymentTracker {
+    pub fn new(retention: Duration) -> Self {
+        Self {
+            deployments: DashMap::new(),
+            retention,
+        }
+    }
+
+    /// Start tracking an upload, refusing the ID of one still in progress
+    pub fn begin(&self, upload_id: &str, dot_name: &str, expected_bytes: Option<u64>) -> ApiResult<()> {
+        self.prune();
+
+        let now = Utc::now();
+        let status = DeploymentStatus {
+            upload_id: upload_id.to_string(),
+            dot_name: dot_name.to_string(),
+            state: DeploymentState::Uploading,
+            bytes_received: 0,
+            expected_bytes,
+            dot_id: None,
+            error: None,
+            started_at: now,
+            updated_at: now,
+        };
+        match self.deployments.entry(upload_id.to_string()) {
+            Entry::Occupied(entry) if is_running(entry.get()) => Err(ApiError::Conflict {
+                message: format!("Upload {} is already in progress", upload_id),
+            }),
+            Entry::Occupied(mut entry) => {
+                entry.insert(status);
+                Ok(())
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(status);
+                Ok(())
+            }
+        }
+    }
+
+    /// Record the bytecode received so far
+    pub fn record_progress(&self, upload_id: &str, bytes_received: u64) {
+        self.update(upload_id, |status| status.bytes_received = bytes_received);
+    }
+
+    /// Record that all bytecode arrived and the runtime is deploying it
+    pub fn mark_deploying(&self, upload_id: &str) {
+        self.update(upload_id, |status| status.state = DeploymentState::Deploying);
+    }
+
+    /// Record how the deployment ended
+    pub fn finish(&self, upload_id: &str, result: Result<&str, &ApiError>) {
+        self.update(upload_id, |status| match result {
+            Ok(dot_id) => {
+                status.state = DeploymentState::Deployed;
+                status.dot_id = Some(dot_id.to_string());
+            }
+            Err(e) => {
+                status.state = DeploymentState::Failed;
+                status.error = Some(e.to_string());
+            }
+        });
+    }
+
+    pub fn get(&self, upload_id: &str) -> Option<DeploymentStatus> {
+        self.deployments.get(upload_id).map(|status| status.clone())
+    }
+
+    fn update(&self, upload_id: &str, update: impl FnOnce(&mut DeploymentStatus)) {
+        if let Some(mut status) = self.deployments.get_mut(upload_id) {
+            update(&mut status);
+            status.updated_at = Utc::now();
+        }
+    }
+
+    /// Forget finished deployments older than the retention period
+    fn prune(&self) {
+        let now = Utc::now();
+        self.deployments
+            .retain(|_, status| is_running(status) || (now - status.updated_at).to_std().map_or(true, |age| age < self.retention));
+    }
+}
+
+impl Default for DeploymentTracker {
+    fn default() -> Self {
+        Self::new(UploadConfig::default().status_retention)
+    }
+}
+
+fn is_running(status: &DeploymentStatus) -> bool {
+    matches!(status.state, DeploymentState::Uploading | DeploymentState::Deploying)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::StreamBody;
+    use hyper::body::Frame;
+    use std::convert::Infallible;
+
+    const BODY: &[u8] = b"preamble\r\n--XyZ\r\nContent-Disposition: form-data; name=\"metadata\"\r\nContent-Type: application/json\r\n\r\n{\"name\":\"big\"}\r\n--XyZ\r\nContent-Disposition: form-data; name=\"bytecode\"; filename=\"big.bin\"\r\n\r\n0123456789\r\n-\r\n--XyZ--\r\n";
+
+    fn body(bytes: &'static [u8], frame_size: usize) -> StreamBody<impl futures::Stream<Item = Result<Frame<Bytes>, Infallible>> + Unpin> {
+        StreamBody::new(futures::stream::iter(bytes.chunks(frame_size).map(|frame| Ok(Frame::data(Bytes::from_static(frame))))))
+    }
+
+    #[tokio::test]
+    async fn test_parts_are_read_in_fixed_size_chunks() {
+        // Frames of every size split the delimiters at different points
+        for frame_size in 1..BODY.len() {
+            let mut upload = MultipartUpload::new(body(BODY, frame_size), "XyZ", 4);
+
+            assert_eq!(upload.next_part().await.unwrap().as_deref(), Some("metadata"));
+            assert_eq!(upload.read_part(MAX_METADATA_BYTES).await.unwrap(), br#"{"name":"big"}"#);
+
+            assert_eq!(upload.next_part().await.unwrap().as_deref(), Some("bytecode"));
+            let mut chunks = Vec::new();
+            while let Some(chunk) = upload.next_chunk().await.unwrap() {
+                chunks.push(chunk);
+            }
+            assert_eq!(chunks, ["0123", "4567", "89\r\n", "-"].map(|chunk| Bytes::from_static(chunk.as_bytes())));
+
+            assert_eq!(upload.next_part().await.unwrap(), None);
+            assert_eq!(upload.body_digest(), ring::digest::digest(&SHA256, BODY).as_ref());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_truncated_body_is_rejected() {
+        let truncated = &BODY[..BODY.len() - 20];
+        let mut upload = MultipartUpload::new(body(truncated, 7), "XyZ", 4);
+
+        upload.next_part().await.unwrap();
+        upload.next_part().await.unwrap();
+        let error = loop {
+            match upload.next_chunk().await {
+                Ok(Some(_)) => continue,
+                Ok(None) => panic!("a truncated part must not end cleanly"),
+                Err(e) => break e,
+            }
+        };
+        assert!(matches!(error, ApiError::BadRequest { .. }));
+    }
+
+    #[test]
+    fn test_tracker_refuses_running_upload_ids() {
+        let tracker = DeploymentTracker::default();
+        tracker.begin("upload-1", "big", Some(100)).unwrap();
+        tracker.record_progress("upload-1", 40);
+        assert!(matches!(tracker.begin("upload-1", "big", None), Err(ApiError::Conflict { .. })));
+
+        let status = tracker.get("upload-1").unwrap();
+        assert_eq!((status.state, status.bytes_received), (DeploymentState::Uploading, 40));
+
+        // A finished upload's ID may be reused
+        tracker.finish("upload-1", Err(&ApiError::BadRequest { message: "interrupted".to_string() }));
+        assert_eq!(tracker.get("upload-1").unwrap().state, DeploymentState::Failed);
+        tracker.begin("upload-1", "big", None).unwrap();
+        assert_eq!(tracker.get("upload-1").unwrap().bytes_received, 0);
+    }
+}
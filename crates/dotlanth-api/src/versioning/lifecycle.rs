@@ -0,0 +1,535 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+//! Lifecycle of the REST API's path versions
+//!
+//! Clients pick a version with the path prefix (`/api/v2/...`) or, on unversioned
+//! paths (`/api/...`), with an `Accept` media type such as
+//! `application/vnd.dotlanth.v2+json` or a `version=2` parameter; without either
+//! they get the newest current version. The path prefix wins when both are given.
+//!
+//! Routes declare the range of versions they serve, so an endpoint unchanged in a
+//! new version needs no second handler: requests are rewritten to the prefix of the
+//! version that introduced the route before they are dispatched. Deprecated
+//! versions answer with `Deprecation`, `Sunset` and `Link` headers; removed ones, or
+//! ones past their sunset date, answer 410 with a link to the migration guide.
+
+use crate::error::{ApiError, ApiResult};
+use crate::grpc_pool::parse_env;
+use crate::openapi;
+use chrono::{DateTime, NaiveDate, Utc};
+use hyper::Request;
+use hyper::header::{ACCEPT, HeaderMap, HeaderValue, LINK};
+use hyper::http::uri::{PathAndQuery, Uri};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use thiserror::Error;
+use tracing::warn;
+
+/// Vendor media type prefix selecting a version, as in `application/vnd.dotlanth.v2+json`
+const VENDOR_MEDIA_TYPE_PREFIX: &str = "application/vnd.dotlanth.v";
+
+/// Lifecycle status of an API version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionStatus {
+    Current,
+    Deprecated,
+    Removed,
+}
+
+impl std::str::FromStr for VersionStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "current" => Ok(Self::Current),
+            "deprecated" => Ok(Self::Deprecated),
+            "removed" => Ok(Self::Removed),
+            other => Err(format!("unknown version status '{}'", other)),
+        }
+    }
+}
+
+/// Settings of one API version
+#[derive(Debug, Clone)]
+pub struct PathVersionConfig {
+    /// Version number, e.g. 2 for `/api/v2`
+    pub version: u32,
+    pub status: VersionStatus,
+    /// When the version was or will be deprecated; a deprecated version without
+    /// one reports the time the gateway started
+    pub deprecated_at: Option<DateTime<Utc>>,
+    /// When the version stops being served
+    pub sunset_at: Option<DateTime<Utc>>,
+}
+
+impl PathVersionConfig {
+    pub fn new(version: u32, status: VersionStatus) -> Self {
+        Self {
+            version,
+            status,
+            deprecated_at: None,
+            sunset_at: None,
+        }
+    }
+}
+
+/// API version settings
+#[derive(Debug, Clone)]
+pub struct ApiVersionsConfig {
+    pub versions: Vec<PathVersionConfig>,
+    /// Guide linked from deprecation headers and from responses for removed versions
+    pub migration_guide_url: String,
+}
+
+impl Default for ApiVersionsConfig {
+    fn default() -> Self {
+        Self {
+            versions: vec![PathVersionConfig::new(1, VersionStatus::Current)],
+            migration_guide_url: "https://docs.dotlanth.com/api/migration".to_string(),
+        }
+    }
+}
+
+impl ApiVersionsConfig {
+    /// Load API version settings from `DOTLANTH_API_*` variables
+    ///
+    /// `DOTLANTH_API_VERSIONS` lists the versions as `v1=deprecated,v2` (status
+    /// `current`, `deprecated` or `removed`, default `current`).
+    /// `DOTLANTH_API_V<n>_DEPRECATED_AT` and `DOTLANTH_API_V<n>_SUNSET_AT` take an
+    /// RFC 3339 timestamp or a `YYYY-MM-DD` date.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Some(versions) = parse_env::<String>("DOTLANTH_API_VERSIONS") {
+            config.versions = parse_versions(&versions);
+        }
+        for version in &mut config.versions {
+            version.deprecated_at = parse_env::<String>(&format!("DOTLANTH_API_V{}_DEPRECATED_AT", version.version)).and_then(|value| parse_date(&value));
+            version.sunset_at = parse_env::<String>(&format!("DOTLANTH_API_V{}_SUNSET_AT", version.version)).and_then(|value| parse_date(&value));
+        }
+        if let Some(url) = parse_env("DOTLANTH_API_MIGRATION_GUIDE_URL") {
+            config.migration_guide_url = url;
+        }
+
+        config
+    }
+}
+
+/// Parse a `v1=deprecated,v2` version list, skipping malformed entries
+fn parse_versions(versions: &str) -> Vec<PathVersionConfig> {
+    versions
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let (label, status) = entry.split_once('=').unwrap_or((entry, "current"));
+            let version = label.trim().strip_prefix('v').and_then(|number| number.parse().ok());
+            match (version, status.trim().parse()) {
+                (Some(version), Ok(status)) => Some(PathVersionConfig::new(version, status)),
+                _ => {
+                    warn!("Ignoring malformed API version entry: {}", entry);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    let parsed = DateTime::parse_from_rfc3339(value)
+        .map(|date| date.with_timezone(&Utc))
+        .ok()
+        .or_else(|| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok().and_then(|date| date.and_hms_opt(0, 0, 0)).map(|date| date.and_utc()));
+    if parsed.is_none() {
+        warn!("Ignoring invalid API version date '{}'", value);
+    }
+    parsed
+}
+
+/// An API version configuration that cannot be served
+#[derive(Debug, Error, PartialEq)]
+pub enum VersionConfigError {
+    #[error("no API version is configured as current or deprecated")]
+    NothingServed,
+
+    #[error("API version v{0} is configured more than once")]
+    Duplicate(u32),
+
+    #[error("API version v{0} has a sunset date before its deprecation date")]
+    SunsetBeforeDeprecation(u32),
+}
+
+#[derive(Debug)]
+struct PathVersion {
+    status: VersionStatus,
+    deprecated_at: Option<DateTime<Utc>>,
+    sunset_at: Option<DateTime<Utc>>,
+    requests: AtomicU64,
+}
+
+impl PathVersion {
+    /// Status at `now`, with the configured dates moving the version along
+    fn status_at(&self, now: DateTime<Utc>) -> VersionStatus {
+        if self.status == VersionStatus::Removed || self.sunset_at.is_some_and(|sunset| sunset <= now) {
+            VersionStatus::Removed
+        } else if self.status == VersionStatus::Deprecated || self.deprecated_at.is_some_and(|deprecated| deprecated <= now) {
+            VersionStatus::Deprecated
+        } else {
+            VersionStatus::Current
+        }
+    }
+}
+
+/// The API version a request was served under, attached to the request's extensions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestedVersion(pub u32);
+
+/// One version in the discovery document
+#[derive(Debug, Serialize)]
+pub struct VersionSummary {
+    pub version: String,
+    pub status: VersionStatus,
+    pub deprecated_at: Option<DateTime<Utc>>,
+    pub sunset_at: Option<DateTime<Utc>>,
+    /// Requests served under this version since the gateway started
+    pub requests: u64,
+}
+
+/// Response of `GET /versions`
+#[derive(Debug, Serialize)]
+pub struct VersionsDocument {
+    pub versions: Vec<VersionSummary>,
+    /// Version served to requests that do not ask for one
+    pub default_version: Option<String>,
+    pub migration_guide: String,
+}
+
+/// Configured API versions with their request counters
+#[derive(Debug)]
+pub struct ApiVersions {
+    versions: BTreeMap<u32, PathVersion>,
+    migration_guide_url: String,
+}
+
+impl ApiVersions {
+    /// Validate the configured versions
+    pub fn new(config: &ApiVersionsConfig) -> Result<Self, VersionConfigError> {
+        let started = Utc::now();
+        let mut versions = BTreeMap::new();
+        for version in &config.versions {
+            if let (Some(deprecated_at), Some(sunset_at)) = (version.deprecated_at, version.sunset_at)
+                && sunset_at < deprecated_at
+            {
+                return Err(VersionConfigError::SunsetBeforeDeprecation(version.version));
+            }
+            let path_version = PathVersion {
+                status: version.status,
+                deprecated_at: version.deprecated_at.or((version.status == VersionStatus::Deprecated).then_some(started)),
+                sunset_at: version.sunset_at,
+                requests: AtomicU64::new(0),
+            };
+            if versions.insert(version.version, path_version).is_some() {
+                return Err(VersionConfigError::Duplicate(version.version));
+            }
+        }
+        if versions.values().all(|version| version.status == VersionStatus::Removed) {
+            return Err(VersionConfigError::NothingServed);
+        }
+
+        Ok(Self {
+            versions,
+            migration_guide_url: config.migration_guide_url.clone(),
+        })
+    }
+
+    /// Version served to requests that do not ask for one: the newest current
+    /// version, or the newest deprecated one if none is current
+    pub fn default_version(&self) -> Option<u32> {
+        let now = Utc::now();
+        let newest = |status| self.versions.iter().rev().find(|(_, version)| version.status_at(now) == status).map(|(number, _)| *number);
+        newest(VersionStatus::Current).or_else(|| newest(VersionStatus::Deprecated))
+    }
+
+    /// Requests served under a version since the gateway started
+    pub fn request_count(&self, version: u32) -> u64 {
+        self.versions.get(&version).map_or(0, |version| version.requests.load(Ordering::Relaxed))
+    }
+
+    /// Resolve the version of an `/api/` request and rewrite its path to the prefix
+    /// its route is dispatched under
+    ///
+    /// Returns `None` for paths outside `/api/`. Unknown versions are rejected with
+    /// 404 and removed ones with 410.
+    pub fn resolve<B>(&self, req: &mut Request<B>) -> ApiResult<Option<u32>> {
+        let path = req.uri().path();
+        let (version, rest) = match openapi::split_version_prefix(path) {
+            Some((version, rest)) => (version, rest.to_string()),
+            None => {
+                let Some(rest) = path.strip_prefix("/api/") else {
+                    return Ok(None);
+                };
+                let version = accepted_version(req.headers()).or_else(|| self.default_version()).ok_or_else(|| ApiError::NotFound {
+                    message: "No API version is available".to_string(),
+                })?;
+                (version, format!("/{}", rest))
+            }
+        };
+
+        let Some(path_version) = self.versions.get(&version) else {
+            return Err(ApiError::NotFound {
+                message: format!("API version v{} does not exist; supported versions: {}", version, self.supported_labels()),
+            });
+        };
+        if path_version.status_at(Utc::now()) == VersionStatus::Removed {
+            return Err(ApiError::Gone {
+                message: format!("API version v{} has been removed; supported versions: {}", version, self.supported_labels()),
+                migration_guide: self.migration_guide_url.clone(),
+            });
+        }
+        path_version.requests.fetch_add(1, Ordering::Relaxed);
+
+        let dispatch_version = openapi::find_versioned_route(req.method(), version, &rest).map_or(version, |route| route.since);
+        let path_and_query = match req.uri().query() {
+            Some(query) => format!("/api/v{}{}?{}", dispatch_version, rest, query),
+            None => format!("/api/v{}{}", dispatch_version, rest),
+        };
+        let mut parts = req.uri().clone().into_parts();
+        parts.path_and_query = Some(PathAndQuery::try_from(path_and_query).map_err(|e| ApiError::BadRequest {
+            message: format!("Invalid request path: {}", e),
+        })?);
+        *req.uri_mut() = Uri::from_parts(parts).map_err(|e| ApiError::BadRequest {
+            message: format!("Invalid request URI: {}", e),
+        })?;
+        req.extensions_mut().insert(RequestedVersion(version));
+
+        Ok(Some(version))
+    }
+
+    /// Add deprecation headers to a response served under a deprecated version
+    pub fn apply_headers(&self, version: u32, headers: &mut HeaderMap) {
+        let Some(path_version) = self.versions.get(&version).filter(|path_version| path_version.status_at(Utc::now()) == VersionStatus::Deprecated) else {
+            return;
+        };
+
+        // RFC 9745: a structured-field date, seconds since the epoch
+        if let Some(deprecated_at) = path_version.deprecated_at
+            && let Ok(value) = HeaderValue::from_str(&format!("@{}", deprecated_at.timestamp()))
+        {
+            headers.insert("deprecation", value);
+        }
+        // RFC 8594: an HTTP-date
+        if let Some(sunset_at) = path_version.sunset_at
+            && let Ok(value) = HeaderValue::from_str(&sunset_at.format("%a, %d %b %Y %H:%M:%S GMT").to_string())
+        {
+            headers.insert("sunset", value);
+        }
+        if let Ok(value) = HeaderValue::from_str(&format!("<{}>; rel=\"deprecation\"", self.migration_guide_url)) {
+            headers.append(LINK, value);
+        }
+    }
+
+    /// Versions with their status and request counts, newest first
+    pub fn discovery(&self) -> VersionsDocument {
+        let now = Utc::now();
+        VersionsDocument {
+            versions: self
+                .versions
+                .iter()
+                .rev()
+                .map(|(number, version)| VersionSummary {
+                    version: format!("v{}", number),
+                    status: version.status_at(now),
+                    deprecated_at: version.deprecated_at,
+                    sunset_at: version.sunset_at,
+                    requests: version.requests.load(Ordering::Relaxed),
+                })
+                .collect(),
+            default_version: self.default_version().map(|version| format!("v{}", version)),
+            migration_guide: self.migration_guide_url.clone(),
+        }
+    }
+
+    fn supported_labels(&self) -> String {
+        let now = Utc::now();
+        self.versions
+            .iter()
+            .filter(|(_, version)| version.status_at(now) != VersionStatus::Removed)
+            .map(|(number, _)| format!("v{}", number))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl Default for ApiVersions {
+    fn default() -> Self {
+        Self::new(&ApiVersionsConfig::default()).expect("default API versions are valid")
+    }
+}
+
+/// Version requested through the `Accept` header, if any
+fn accepted_version(headers: &HeaderMap) -> Option<u32> {
+    let accept = headers.get(ACCEPT)?.to_str().ok()?;
+    accept.split(',').find_map(|media_range| {
+        let mut parts = media_range.split(';').map(str::trim);
+        let media_type = parts.next()?;
+        let vendor = media_type
+            .strip_prefix(VENDOR_MEDIA_TYPE_PREFIX)
+            .and_then(|rest| rest.split('+').next())
+            .and_then(|number| number.parse().ok());
+        vendor.or_else(|| {
+            parts
+                .filter_map(|parameter| parameter.split_once('='))
+                .find(|(name, _)| name.trim().eq_ignore_ascii_case("version"))
+                .and_then(|(_, value)| value.trim().trim_matches('"').trim_start_matches('v').parse().ok())
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use hyper::{Method, StatusCode};
+
+    fn versions(entries: &[(u32, VersionStatus)]) -> ApiVersions {
+        ApiVersions::new(&ApiVersionsConfig {
+            versions: entries.iter().map(|(version, status)| PathVersionConfig::new(*version, *status)).collect(),
+            ..ApiVersionsConfig::default()
+        })
+        .unwrap()
+    }
+
+    fn request(method: Method, uri: &str, accept: Option<&str>) -> Request<()> {
+        let mut req = Request::builder().method(method).uri(uri);
+        if let Some(accept) = accept {
+            req = req.header(ACCEPT, accept);
+        }
+        req.body(()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_versions() {
+        let parsed = parse_versions("v1=deprecated, v2, v3=removed, 4, v5=retired");
+        let parsed: Vec<_> = parsed.iter().map(|version| (version.version, version.status)).collect();
+        assert_eq!(parsed, vec![(1, VersionStatus::Deprecated), (2, VersionStatus::Current), (3, VersionStatus::Removed)]);
+        assert_eq!(parse_date("2026-07-01").unwrap().to_rfc3339(), "2026-07-01T00:00:00+00:00");
+    }
+
+    #[test]
+    fn test_path_and_accept_versioning() {
+        let api = versions(&[(1, VersionStatus::Deprecated), (2, VersionStatus::Current)]);
+
+        // Routes introduced in v1 serve v2 under their original prefix
+        let mut req = request(Method::GET, "/api/v2/vm/dots/abc/state?field=x", None);
+        assert_eq!(api.resolve(&mut req).unwrap(), Some(2));
+        assert_eq!(req.uri().to_string(), "/api/v1/vm/dots/abc/state?field=x");
+        assert_eq!(req.extensions().get::<RequestedVersion>(), Some(&RequestedVersion(2)));
+
+        let mut req = request(Method::GET, "/api/vm/dots", Some("text/html, application/vnd.dotlanth.v1+json"));
+        assert_eq!(api.resolve(&mut req).unwrap(), Some(1));
+        assert_eq!(req.uri().path(), "/api/v1/vm/dots");
+
+        let mut req = request(Method::GET, "/api/vm/dots", Some("application/json; version=1"));
+        assert_eq!(api.resolve(&mut req).unwrap(), Some(1));
+
+        // The path prefix wins over the Accept header, and no preference gets the newest current version
+        let mut req = request(Method::GET, "/api/v2/vm/dots", Some("application/vnd.dotlanth.v1+json"));
+        assert_eq!(api.resolve(&mut req).unwrap(), Some(2));
+        let mut req = request(Method::GET, "/api/vm/dots", None);
+        assert_eq!(api.resolve(&mut req).unwrap(), Some(2));
+
+        let mut req = request(Method::GET, "/graphql", None);
+        assert_eq!(api.resolve(&mut req).unwrap(), None);
+
+        assert_eq!(api.request_count(1), 2);
+        assert_eq!(api.request_count(2), 3);
+    }
+
+    #[test]
+    fn test_unknown_and_removed_versions() {
+        let api = versions(&[(1, VersionStatus::Removed), (2, VersionStatus::Current)]);
+
+        let err = api.resolve(&mut request(Method::GET, "/api/v3/vm/dots", None)).unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+
+        let err = api.resolve(&mut request(Method::GET, "/api/v1/vm/dots", None)).unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::GONE);
+        let response = hyper::Response::from(err);
+        assert_eq!(response.status(), StatusCode::GONE);
+        assert_eq!(api.request_count(1), 0);
+
+        // A passed sunset date removes a version
+        let sunset = ApiVersions::new(&ApiVersionsConfig {
+            versions: vec![
+                PathVersionConfig {
+                    sunset_at: Some(Utc::now() - Duration::days(1)),
+                    deprecated_at: Some(Utc::now() - Duration::days(30)),
+                    ..PathVersionConfig::new(1, VersionStatus::Deprecated)
+                },
+                PathVersionConfig::new(2, VersionStatus::Current),
+            ],
+            ..ApiVersionsConfig::default()
+        })
+        .unwrap();
+        let err = sunset.resolve(&mut request(Method::GET, "/api/v1/vm/dots", None)).unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::GONE);
+    }
+
+    #[test]
+    fn test_deprecation_headers() {
+        let deprecated_at = DateTime::parse_from_rfc3339("2026-01-01T00:00:00Z").unwrap().with_timezone(&Utc);
+        let sunset_at = Utc::now() + Duration::days(30);
+        let api = ApiVersions::new(&ApiVersionsConfig {
+            versions: vec![
+                PathVersionConfig {
+                    deprecated_at: Some(deprecated_at),
+                    sunset_at: Some(sunset_at),
+                    ..PathVersionConfig::new(1, VersionStatus::Current)
+                },
+                PathVersionConfig::new(2, VersionStatus::Current),
+            ],
+            ..ApiVersionsConfig::default()
+        })
+        .unwrap();
+
+        let mut headers = HeaderMap::new();
+        api.apply_headers(1, &mut headers);
+        assert_eq!(headers["deprecation"], format!("@{}", deprecated_at.timestamp()).as_str());
+        assert!(headers["sunset"].to_str().unwrap().ends_with(" GMT"));
+        assert!(headers[LINK].to_str().unwrap().contains("rel=\"deprecation\""));
+
+        let mut headers = HeaderMap::new();
+        api.apply_headers(2, &mut headers);
+        assert!(headers.is_empty());
+
+        let document = api.discovery();
+        assert_eq!(document.default_version.as_deref(), Some("v2"));
+        assert_eq!(document.versions[1].status, VersionStatus::Deprecated);
+    }
+
+    #[test]
+    fn test_invalid_configurations() {
+        let config = |versions: Vec<PathVersionConfig>| ApiVersionsConfig {
+            versions,
+            ..ApiVersionsConfig::default()
+        };
+
+        assert_eq!(
+            ApiVersions::new(&config(vec![PathVersionConfig::new(1, VersionStatus::Removed)])).unwrap_err(),
+            VersionConfigError::NothingServed
+        );
+        assert_eq!(
+            ApiVersions::new(&config(vec![PathVersionConfig::new(1, VersionStatus::Current), PathVersionConfig::new(1, VersionStatus::Deprecated)])).unwrap_err(),
+            VersionConfigError::Duplicate(1)
+        );
+        let backwards = PathVersionConfig {
+            deprecated_at: Some(Utc::now()),
+            sunset_at: Some(Utc::now() - Duration::days(1)),
+            ..PathVersionConfig::new(1, VersionStatus::Deprecated)
+        };
+        assert_eq!(ApiVersions::new(&config(vec![backwards])).unwrap_err(), VersionConfigError::SunsetBeforeDeprecation(1));
+    }
+}
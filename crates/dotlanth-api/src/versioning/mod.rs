@@ -18,12 +18,14 @@
 
 pub mod compatibility;
 pub mod deprecation;
+pub mod lifecycle;
 pub mod negotiation;
 pub mod schema;
 pub mod version;
 
 pub use compatibility::*;
 pub use deprecation::*;
+pub use lifecycle::*;
 pub use negotiation::*;
 pub use schema::*;
 pub use version::*;
@@ -16,13 +16,26 @@
 
 //! VM client for interacting with the DotVM runtime via gRPC
 
+use crate::db::pipeline::RuntimeStateBackend;
 use crate::error::{ApiError, ApiResult};
-use crate::models::{DeployDotRequest, DeployDotResponse, DotState, DotStatus, ExecuteDotRequest, ExecuteDotResponse, ExecutionStatus, ValidationResult};
+use crate::grpc_pool::{ChannelPool, GrpcTlsConfig, Idempotency, PoolConfig, PoolStats, RuntimeInterceptor};
+use crate::models::{
+    AbiFieldType, AbiInputField, DeployDotRequest, DeployDotResponse, DeployMetadata, DotEvent, DotInputSchema, DotState, DotStatus, ExecuteDotRequest, ExecuteDotResponse, ExecutionStatus,
+    MetricDataPoint, PayloadFilter, StateChange, StateChangeKind, StateDiff, StateValue, ValidationResult, VmMetric,
+};
 use base64::Engine;
 use chrono::Utc;
+use dotvm_common::log_capture::{LOG_SHIPPING_TARGET, LogEvent, LogEvents};
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt};
+use hyper::body::Bytes;
+use ring::digest;
 use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tonic::service::interceptor::InterceptedService;
 use tonic::transport::Channel;
-use tracing::{error, info, warn};
+use tracing::{Instrument, error, info, info_span, warn};
 use uuid::Uuid;
 
 // Import generated gRPC client
@@ -30,63 +43,191 @@ mod proto {
     tonic::include_proto!("vm_service");
 }
 
+use proto::deploy_dot_chunk;
 use proto::vm_service_client::VmServiceClient;
 
+/// gRPC client that presents the gateway's API key and forwards the current request's trace context
+fn vm_client(channel: Channel, interceptor: RuntimeInterceptor) -> VmServiceClient<InterceptedService<Channel, RuntimeInterceptor>> {
+    VmServiceClient::with_interceptor(channel, interceptor)
+}
+
+/// Captured events forwarded per IngestLogs call
+const LOG_FORWARD_BATCH: usize = 256;
+
+/// A captured event as a log record emitted on `node`
+fn log_record(event: LogEvent, node: &str) -> proto::LogRecord {
+    proto::LogRecord {
+        id: String::new(),
+        timestamp_ns: event.timestamp_ns,
+        level: event.level,
+        target: event.target,
+        message: event.message,
+        dot_id: event.dot_id.unwrap_or_default(),
+        node: node.to_string(),
+        service: event.service,
+        fields: event.fields.into_iter().collect(),
+        sequence: 0,
+    }
+}
+
+/// Request deploying `dot_source` under `name`, with the settings used for every gateway deployment
+fn deploy_request(name: &str, dot_source: String) -> proto::DeployDotRequest {
+    proto::DeployDotRequest {
+        dot_name: name.to_string(),
+        dot_source,
+        metadata: Some(proto::DotMetadata {
+            version: "1.0.0".to_string(),
+            description: "Deployed via API Gateway".to_string(),
+            author: "api-gateway".to_string(),
+            tags: vec![],
+            license: "AGPL-3.0".to_string(),
+            custom_fields: HashMap::new(),
+            dependencies: vec![],
+            deterministic: false,
+        }),
+        deployer_id: "api-gateway".to_string(),
+        options: Some(proto::DeploymentOptions {
+            validate_abi: true,
+            generate_ui: false,
+            // The runtime reads the architecture from the bytecode header
+            target_architecture: String::new(),
+            enable_optimizations: true,
+            stage_only: false,
+        }),
+    }
+}
+
+fn deployed(response: proto::DeployDotResponse) -> ApiResult<DeployDotResponse> {
+    if !response.success {
+        return Err(ApiError::BadRequest {
+            message: format!("Deployment failed: {}", response.error_message),
+        });
+    }
+
+    // Create validation result based on success
+    let validation = ValidationResult {
+        valid: true,
+        errors: vec![],
+        warnings: vec![],
+    };
+
+    info!("Successfully deployed dot: {}", response.dot_id);
+
+    Ok(DeployDotResponse {
+        dot_id: response.dot_id,
+        status: DotStatus::Active,
+        deployed_at: Utc::now(),
+        validation,
+        architecture: response.architecture,
+    })
+}
+
+/// Bytecode framed as data chunks followed by a trailer with its size and checksum
+///
+/// When `bytecode` fails the error is left in `failure` and the stream ends without a
+/// trailer, which makes the runtime discard what it received.
+fn bytecode_chunks<S>(bytecode: S, failure: Arc<parking_lot::Mutex<Option<ApiError>>>) -> impl Stream<Item = proto::DeployDotChunk> + Send + 'static
+where
+    S: Stream<Item = ApiResult<Bytes>> + Send + 'static,
+{
+    let state = Some((Box::pin(bytecode), 0u64, digest::Context::new(&digest::SHA256)));
+    futures::stream::unfold(state, move |state| {
+        let failure = failure.clone();
+        async move {
+            let (mut bytecode, offset, mut checksum) = state?;
+            match bytecode.next().await {
+                Some(Ok(data)) => {
+                    checksum.update(&data);
+                    let next_offset = offset + data.len() as u64;
+                    let chunk = deploy_dot_chunk::Payload::Data(proto::BytecodeChunk { offset, data: data.to_vec() });
+                    Some((proto::DeployDotChunk { payload: Some(chunk) }, Some((bytecode, next_offset, checksum))))
+                }
+                Some(Err(e)) => {
+                    *failure.lock() = Some(e);
+                    None
+                }
+                None => {
+                    let sha256 = checksum.finish().as_ref().iter().map(|byte| format!("{:02x}", byte)).collect();
+                    let trailer = deploy_dot_chunk::Payload::Trailer(proto::DeployDotTrailer { total_bytes: offset, sha256 });
+                    Some((proto::DeployDotChunk { payload: Some(trailer) }, None))
+                }
+            }
+        }
+    })
+}
+
 /// VM client for interacting with DotVM via gRPC
 #[derive(Clone)]
 pub struct VmClient {
-    client: VmServiceClient<Channel>,
+    pool: Arc<ChannelPool>,
 }
 
 impl VmClient {
-    /// Create a new VM client
+    /// Create a new VM client with the default pool settings
     pub async fn new(vm_endpoint: &str) -> ApiResult<Self> {
-        info!("Connecting to VM service at: {}", vm_endpoint);
+        Self::with_pool_config(vm_endpoint, PoolConfig::default()).await
+    }
 
-        let channel = Channel::from_shared(vm_endpoint.to_string())
-            .map_err(|e| ApiError::InternalServerError {
-                message: format!("Invalid VM endpoint: {}", e),
-            })?
-            .connect()
-            .await
-            .map_err(|e| ApiError::InternalServerError {
-                message: format!("Failed to connect to VM service: {}", e),
-            })?;
+    /// Create a new VM client backed by a pool of `pool_config.size` channels
+    pub async fn with_pool_config(vm_endpoint: &str, pool_config: PoolConfig) -> ApiResult<Self> {
+        info!("Connecting to VM service at: {}", vm_endpoint);
 
-        let client = VmServiceClient::new(channel);
+        let pool = ChannelPool::connect(vm_endpoint, pool_config).await?;
 
         info!("Successfully connected to VM service");
 
-        Ok(Self { client })
+        Ok(Self { pool: Arc::new(pool) })
+    }
+
+    /// Create a client without dialing; channels connect on first use
+    pub fn connect_lazy(vm_endpoint: &str, pool_config: PoolConfig) -> ApiResult<Self> {
+        Ok(Self {
+            pool: Arc::new(ChannelPool::connect_lazy(vm_endpoint, pool_config)?),
+        })
+    }
+
+    /// State held by the runtime, written in batches through the same pooled channels
+    pub fn state_backend(&self) -> RuntimeStateBackend {
+        RuntimeStateBackend::new(self.pool.clone())
+    }
+
+    /// Channel pool state and retry counters
+    pub fn pool_stats(&self) -> PoolStats {
+        self.pool.stats()
+    }
+
+    /// TLS settings used to reach the VM service
+    pub fn tls(&self) -> &GrpcTlsConfig {
+        self.pool.tls()
+    }
+
+    /// Start probing pooled channels with the HealthCheck RPC, re-dialing broken ones
+    pub fn spawn_health_checker(&self) -> JoinHandle<()> {
+        let interceptor = self.pool.interceptor();
+        self.pool.spawn_health_checker(move |channel| {
+            let mut client = vm_client(channel, interceptor.clone());
+            async move {
+                let request = proto::HealthCheckRequest {
+                    services: vec![],
+                    include_details: false,
+                };
+                client.health_check(request).await.is_ok()
+            }
+        })
     }
 
     /// Deploy a new dot
     pub async fn deploy_dot(&self, request: DeployDotRequest) -> ApiResult<DeployDotResponse> {
         info!("Deploying dot: {}", request.name);
 
-        let grpc_request = proto::DeployDotRequest {
-            dot_name: request.name.clone(),
-            dot_source: request.bytecode.clone(),
-            metadata: Some(proto::DotMetadata {
-                version: "1.0.0".to_string(),
-                description: "Deployed via API Gateway".to_string(),
-                author: "api-gateway".to_string(),
-                tags: vec![],
-                license: "AGPL-3.0".to_string(),
-                custom_fields: HashMap::new(),
-            }),
-            deployer_id: "api-gateway".to_string(),
-            options: Some(proto::DeploymentOptions {
-                validate_abi: true,
-                generate_ui: false,
-                target_architecture: "WASM".to_string(),
-                enable_optimizations: true,
-            }),
-        };
+        let grpc_request = deploy_request(&request.name, request.bytecode.clone());
 
-        let mut client = self.client.clone();
-        let response = client
-            .deploy_dot(grpc_request)
+        let response = self
+            .pool
+            .call(Idempotency::NonIdempotent, |channel| {
+                let request = grpc_request.clone();
+                async move { vm_client(channel, self.pool.interceptor()).deploy_dot(request).await }
+            })
             .await
             .map_err(|e| {
                 error!("gRPC deploy_dot call failed: {}", e);
@@ -96,27 +237,45 @@ impl VmClient {
             })?
             .into_inner();
 
-        if !response.success {
-            return Err(ApiError::BadRequest {
-                message: format!("Deployment failed: {}", response.error_message),
-            });
-        }
+        deployed(response)
+    }
 
-        // Create validation result based on success
-        let validation = ValidationResult {
-            valid: true,
-            errors: vec![],
-            warnings: vec![],
+    /// Deploy a dot whose bytecode arrives in chunks, streaming each chunk to the runtime as
+    /// it comes in
+    ///
+    /// The runtime only deploys once the whole bytecode and its checksum have arrived: when
+    /// `bytecode` yields an error the stream is cut short, nothing is deployed and that error
+    /// is returned. The call is not retried, as the bytecode cannot be read again.
+    pub async fn deploy_dot_stream<S>(&self, metadata: &DeployMetadata, bytecode: S) -> ApiResult<DeployDotResponse>
+    where
+        S: Stream<Item = ApiResult<Bytes>> + Send + 'static,
+    {
+        info!("Deploying dot from a streamed upload: {}", metadata.name);
+
+        let header = proto::DeployDotChunk {
+            payload: Some(deploy_dot_chunk::Payload::Header(deploy_request(&metadata.name, String::new()))),
         };
+        let failure = Arc::new(parking_lot::Mutex::new(None));
+        let chunks = futures::stream::iter([header]).chain(bytecode_chunks(bytecode, failure.clone()));
 
-        info!("Successfully deployed dot: {}", response.dot_id);
+        let result = vm_client(self.pool.channel(), self.pool.interceptor()).deploy_dot_stream(chunks).await;
+        if let Some(e) = failure.lock().take() {
+            return Err(e);
+        }
+        let response = result
+            .map_err(|e| match e.code() {
+                tonic::Code::ResourceExhausted => ApiError::PayloadTooLarge { message: e.message().to_string() },
+                tonic::Code::InvalidArgument | tonic::Code::DataLoss => ApiError::BadRequest { message: e.message().to_string() },
+                _ => {
+                    error!("gRPC deploy_dot_stream call failed: {}", e);
+                    ApiError::InternalServerError {
+                        message: format!("gRPC call failed: {}", e),
+                    }
+                }
+            })?
+            .into_inner();
 
-        Ok(DeployDotResponse {
-            dot_id: response.dot_id,
-            status: DotStatus::Active,
-            deployed_at: Utc::now(),
-            validation,
-        })
+        deployed(response)
     }
 
     /// Get dot state
@@ -127,16 +286,27 @@ impl VmClient {
             dot_id: dot_id.to_string(),
             keys: vec![],           // Get all keys
             version: String::new(), // Latest version
+            key_prefix: String::new(),
+            limit: 0, // Server default page size
+            start_after: String::new(),
         };
 
-        let mut client = self.client.clone();
-        let response = client
-            .get_dot_state(grpc_request)
+        let response = self
+            .pool
+            .call(Idempotency::Idempotent, |channel| {
+                let request = grpc_request.clone();
+                async move { vm_client(channel, self.pool.interceptor()).get_dot_state(request).await }
+            })
             .await
-            .map_err(|e| {
-                error!("gRPC get_dot_state call failed: {}", e);
-                ApiError::InternalServerError {
-                    message: format!("gRPC call failed: {}", e),
+            .map_err(|e| match e.code() {
+                tonic::Code::NotFound => ApiError::NotFound { message: e.message().to_string() },
+                tonic::Code::FailedPrecondition => ApiError::Conflict { message: e.message().to_string() },
+                tonic::Code::InvalidArgument => ApiError::BadRequest { message: e.message().to_string() },
+                _ => {
+                    error!("gRPC get_dot_state call failed: {}", e);
+                    ApiError::InternalServerError {
+                        message: format!("gRPC call failed: {}", e),
+                    }
                 }
             })?
             .into_inner();
@@ -166,6 +336,77 @@ impl VmClient {
             state: serde_json::Value::Object(state_json),
             updated_at: Utc::now(), // gRPC response doesn't include timestamps
             version: response.version,
+            architecture: None,
+        })
+    }
+
+    /// Get the changes to a dot's state between two versions
+    pub async fn get_state_diff(&self, dot_id: &str, from: u64, to: u64, start_after: Option<String>, limit: u32, max_value_size: u64) -> ApiResult<StateDiff> {
+        info!("Getting state diff for dot: {} ({} -> {})", dot_id, from, to);
+
+        let grpc_request = proto::GetStateDiffRequest {
+            dot_id: dot_id.to_string(),
+            from_version: from,
+            to_version: to,
+            limit,
+            start_after: start_after.unwrap_or_default(),
+            max_inline_value_size: max_value_size,
+        };
+
+        let response = self
+            .pool
+            .call(Idempotency::Idempotent, |channel| {
+                let request = grpc_request.clone();
+                async move { vm_client(channel, self.pool.interceptor()).get_state_diff(request).await }
+            })
+            .await
+            .map_err(|e| match e.code() {
+                tonic::Code::NotFound => ApiError::NotFound { message: e.message().to_string() },
+                tonic::Code::FailedPrecondition => ApiError::Conflict { message: e.message().to_string() },
+                tonic::Code::InvalidArgument => ApiError::BadRequest { message: e.message().to_string() },
+                _ => {
+                    error!("gRPC get_state_diff call failed: {}", e);
+                    ApiError::InternalServerError {
+                        message: format!("gRPC call failed: {}", e),
+                    }
+                }
+            })?
+            .into_inner();
+
+        let value = |value: proto::StateValue| StateValue {
+            value: match &value.value {
+                Some(proto::state_value::Value::Inline(bytes)) => Some(base64::engine::general_purpose::STANDARD.encode(bytes)),
+                _ => None,
+            },
+            sha256: match value.value {
+                Some(proto::state_value::Value::Sha256(hash)) => Some(hash),
+                _ => None,
+            },
+            size: value.size,
+        };
+        let changes = response
+            .changes
+            .into_iter()
+            .map(|change| StateChange {
+                kind: match change.kind() {
+                    proto::state_change::Kind::Added => StateChangeKind::Added,
+                    proto::state_change::Kind::Removed => StateChangeKind::Removed,
+                    proto::state_change::Kind::Modified => StateChangeKind::Modified,
+                },
+                key: change.key,
+                old_value: change.old_value.map(value),
+                new_value: change.new_value.map(value),
+            })
+            .collect();
+
+        Ok(StateDiff {
+            dot_id: dot_id.to_string(),
+            from_version: response.from_version,
+            to_version: response.to_version,
+            from_root_hash: response.from_root_hash,
+            to_root_hash: response.to_root_hash,
+            changes,
+            next_key: response.has_more.then_some(response.next_key),
         })
     }
 
@@ -186,6 +427,15 @@ impl VmClient {
             inputs.insert(key, value);
         }
 
+        // Named inputs are passed through under their ABI names
+        for (name, value) in &request.inputs {
+            let value = serde_json::to_vec(value).map_err(|e| ApiError::BadRequest {
+                message: format!("Failed to serialize input '{}': {}", name, e),
+            })?;
+
+            inputs.insert(name.clone(), value);
+        }
+
         // Add function name to inputs
         if !request.function.is_empty() {
             inputs.insert("function_name".to_string(), request.function.as_bytes().to_vec());
@@ -201,17 +451,27 @@ impl VmClient {
                 trace_execution: false,
                 timeout_seconds: 30,
                 required_paradots: vec![],
+                bypass_cache: false,
+                record_execution: false,
             }),
+            operation: request.function.clone(),
         };
 
-        let mut client = self.client.clone();
-        let response = client
-            .execute_dot(grpc_request)
+        let response = self
+            .pool
+            .call(Idempotency::NonIdempotent, |channel| {
+                let request = grpc_request.clone();
+                async move { vm_client(channel, self.pool.interceptor()).execute_dot(request).await }
+            })
+            .instrument(info_span!("grpc_call", rpc = "ExecuteDot", dot_id))
             .await
-            .map_err(|e| {
-                error!("gRPC execute_dot call failed: {}", e);
-                ApiError::InternalServerError {
-                    message: format!("gRPC call failed: {}", e),
+            .map_err(|e| match e.code() {
+                tonic::Code::PermissionDenied => ApiError::Forbidden { message: e.message().to_string() },
+                _ => {
+                    error!("gRPC execute_dot call failed: {}", e);
+                    ApiError::InternalServerError {
+                        message: format!("gRPC call failed: {}", e),
+                    }
                 }
             })?
             .into_inner();
@@ -261,9 +521,12 @@ impl VmClient {
             sort_by: String::new(),
         };
 
-        let mut client = self.client.clone();
-        let response = client
-            .list_dots(grpc_request)
+        let response = self
+            .pool
+            .call(Idempotency::Idempotent, |channel| {
+                let request = grpc_request.clone();
+                async move { vm_client(channel, self.pool.interceptor()).list_dots(request).await }
+            })
             .await
             .map_err(|e| {
                 error!("gRPC list_dots call failed: {}", e);
@@ -287,6 +550,7 @@ impl VmClient {
                 state: serde_json::Value::Object(serde_json::Map::new()), // Empty state for list view
                 version: 1,                                               // dot_info doesn't have version field, use default
                 updated_at: Utc::now(),                                   // gRPC response doesn't include timestamps
+                architecture: Some(dot_info.architecture).filter(|architecture| !architecture.is_empty()),
             })
             .collect();
 
@@ -305,9 +569,12 @@ impl VmClient {
             requester_id: "api-gateway".to_string(),
         };
 
-        let mut client = self.client.clone();
-        let response = client
-            .delete_dot(grpc_request)
+        let response = self
+            .pool
+            .call(Idempotency::NonIdempotent, |channel| {
+                let request = grpc_request.clone();
+                async move { vm_client(channel, self.pool.interceptor()).delete_dot(request).await }
+            })
             .await
             .map_err(|e| {
                 error!("gRPC delete_dot call failed: {}", e);
@@ -334,9 +601,12 @@ impl VmClient {
 
         let grpc_request = proto::GetVmStatusRequest { include_details: true };
 
-        let mut client = self.client.clone();
-        let response = client
-            .get_vm_status(grpc_request)
+        let response = self
+            .pool
+            .call(Idempotency::Idempotent, |channel| {
+                let request = grpc_request.clone();
+                async move { vm_client(channel, self.pool.interceptor()).get_vm_status(request).await }
+            })
             .await
             .map_err(|e| {
                 error!("gRPC get_vm_status call failed: {}", e);
@@ -385,9 +655,12 @@ impl VmClient {
 
         let grpc_request = proto::GetArchitecturesRequest {};
 
-        let mut client = self.client.clone();
-        let response = client
-            .get_architectures(grpc_request)
+        let response = self
+            .pool
+            .call(Idempotency::Idempotent, |channel| {
+                let request = grpc_request.clone();
+                async move { vm_client(channel, self.pool.interceptor()).get_architectures(request).await }
+            })
             .await
             .map_err(|e| {
                 error!("gRPC get_architectures call failed: {}", e);
@@ -404,6 +677,210 @@ impl VmClient {
         Ok(architectures)
     }
 
+    /// Get the input declarations from a dot's latest ABI
+    ///
+    /// Returns `None` when the dot has no registered ABI.
+    pub async fn get_dot_abi(&self, dot_id: &str) -> ApiResult<Option<DotInputSchema>> {
+        info!("Getting ABI for dot: {}", dot_id);
+
+        let grpc_request = proto::GetDotAbiRequest {
+            dot_id: dot_id.to_string(),
+            version: String::new(), // Latest version
+        };
+
+        let result = self
+            .pool
+            .call(Idempotency::Idempotent, |channel| {
+                let request = grpc_request.clone();
+                async move { vm_client(channel, self.pool.interceptor()).get_dot_abi(request).await }
+            })
+            .await;
+        let response = match result {
+            Ok(response) => response.into_inner(),
+            Err(e) if e.code() == tonic::Code::NotFound => return Ok(None),
+            Err(e) => {
+                error!("gRPC get_dot_abi call failed: {}", e);
+                return Err(ApiError::InternalServerError {
+                    message: format!("gRPC call failed: {}", e),
+                });
+            }
+        };
+
+        let Some(abi) = response.abi.filter(|_| response.success) else {
+            return Ok(None);
+        };
+
+        let inputs = abi
+            .inputs
+            .into_iter()
+            .map(|field| AbiInputField {
+                field_type: field.field_type.map(abi_field_type).unwrap_or_else(|| AbiFieldType {
+                    name: String::new(),
+                    params: vec![],
+                }),
+                enum_values: field.constraints.map(|c| c.enum_values).unwrap_or_default(),
+                name: field.name,
+                required: field.required,
+            })
+            .collect();
+
+        Ok(Some(DotInputSchema { version: abi.version, inputs }))
+    }
+
+    /// Open a server-side event stream for a single dot
+    ///
+    /// Event type and payload filtering is done by the runtime. Dropping the returned
+    /// stream cancels the underlying gRPC call.
+    pub async fn stream_dot_events(
+        &self,
+        dot_id: &str,
+        event_types: Vec<String>,
+        payload_filters: Vec<PayloadFilter>,
+        last_event_id: Option<String>,
+    ) -> ApiResult<BoxStream<'static, ApiResult<DotEvent>>> {
+        info!("Opening event stream for dot: {}", dot_id);
+
+        self.open_event_stream(vec![dot_id.to_string()], event_types, payload_filters, last_event_id).await
+    }
+
+    /// Open a server-side event stream covering every dot
+    pub async fn stream_all_dot_events(&self, event_types: Vec<String>) -> ApiResult<BoxStream<'static, ApiResult<DotEvent>>> {
+        info!("Opening event stream for all dots");
+
+        self.open_event_stream(vec![], event_types, vec![], None).await
+    }
+
+    async fn open_event_stream(
+        &self,
+        dot_ids: Vec<String>,
+        event_types: Vec<String>,
+        payload_filters: Vec<PayloadFilter>,
+        last_event_id: Option<String>,
+    ) -> ApiResult<BoxStream<'static, ApiResult<DotEvent>>> {
+        let grpc_request = proto::StreamDotEventsRequest {
+            dot_ids,
+            event_types,
+            last_event_id: last_event_id.unwrap_or_default(),
+            from_sequence: 0,
+            payload_filters: payload_filters
+                .into_iter()
+                .map(|filter| proto::PayloadPredicate {
+                    field: filter.field,
+                    equals: filter.equals,
+                })
+                .collect(),
+        };
+
+        let mut client = vm_client(self.pool.channel(), self.pool.interceptor());
+        let stream = client
+            .stream_dot_events(grpc_request)
+            .await
+            .map_err(|e| {
+                error!("gRPC stream_dot_events call failed: {}", e);
+                ApiError::InternalServerError {
+                    message: format!("gRPC call failed: {}", e),
+                }
+            })?
+            .into_inner();
+
+        let events = stream.map(|item| {
+            item.map(|event| DotEvent {
+                data: serde_json::from_slice(&event.event_data).unwrap_or_else(|_| serde_json::Value::String(String::from_utf8_lossy(&event.event_data).to_string())),
+                event_id: event.event_id,
+                dot_id: event.dot_id,
+                event_type: event.event_type,
+                metadata: event.metadata,
+            })
+            .map_err(ApiError::from)
+        });
+
+        Ok(events.boxed())
+    }
+
+    /// Open a stream of VM metric updates, limited to `metric_names` unless empty
+    pub async fn stream_vm_metrics(&self, metric_names: Vec<String>, interval_seconds: u32) -> ApiResult<BoxStream<'static, ApiResult<VmMetric>>> {
+        info!("Opening VM metrics stream");
+
+        let grpc_request = proto::StreamVmMetricsRequest { metric_names, interval_seconds };
+
+        let mut client = vm_client(self.pool.channel(), self.pool.interceptor());
+        let stream = client
+            .stream_vm_metrics(grpc_request)
+            .await
+            .map_err(|e| {
+                error!("gRPC stream_vm_metrics call failed: {}", e);
+                ApiError::InternalServerError {
+                    message: format!("gRPC call failed: {}", e),
+                }
+            })?
+            .into_inner();
+
+        let metrics = stream.map(|item| {
+            item.map(|metric| VmMetric {
+                name: metric.name,
+                metric_type: metric.r#type,
+                data_points: metric
+                    .data_points
+                    .into_iter()
+                    .map(|point| MetricDataPoint {
+                        timestamp: point.timestamp,
+                        value: point.value,
+                    })
+                    .collect(),
+                labels: metric.labels,
+            })
+            .map_err(ApiError::from)
+        });
+
+        Ok(metrics.boxed())
+    }
+
+    /// Ship the gateway's captured log events to the runtime's centralized log store, in
+    /// batches as they arrive, until the capture layer is dropped
+    ///
+    /// Batches the runtime cannot take are dropped rather than retried, so a runtime outage
+    /// cannot hold back requests or grow the gateway's memory.
+    pub fn spawn_log_forwarder(&self, mut events: LogEvents, node: String) -> JoinHandle<()> {
+        let client = self.clone();
+        tokio::spawn(async move {
+            let mut reported_drops = 0;
+            while let Some(event) = events.recv().await {
+                let mut records = vec![log_record(event, &node)];
+                while records.len() < LOG_FORWARD_BATCH {
+                    match events.try_recv() {
+                        Some(event) => records.push(log_record(event, &node)),
+                        None => break,
+                    }
+                }
+                let count = records.len();
+                if let Err(e) = client.ingest_logs(records).await {
+                    warn!(target: LOG_SHIPPING_TARGET, "Failed to forward {} log records: {}", count, e);
+                }
+                let dropped = events.dropped();
+                if dropped > reported_drops {
+                    warn!(target: LOG_SHIPPING_TARGET, "Dropped {} log records while forwarding was behind", dropped - reported_drops);
+                    reported_drops = dropped;
+                }
+            }
+        })
+    }
+
+    /// Store log records in the runtime's centralized log store
+    async fn ingest_logs(&self, records: Vec<proto::LogRecord>) -> ApiResult<u32> {
+        let grpc_request = proto::IngestLogsRequest { records };
+
+        let response = self
+            .pool
+            .call(Idempotency::NonIdempotent, |channel| {
+                let request = grpc_request.clone();
+                async move { vm_client(channel, self.pool.interceptor()).ingest_logs(request).await }
+            })
+            .await
+            .map_err(ApiError::from)?;
+
+        Ok(response.into_inner().accepted)
+    }
+
     /// Health check for VM connection
     pub async fn health_check(&self) -> ApiResult<bool> {
         let grpc_request = proto::HealthCheckRequest {
@@ -411,7 +888,7 @@ impl VmClient {
             include_details: false,
         };
 
-        let mut client = self.client.clone();
+        let mut client = vm_client(self.pool.channel(), self.pool.interceptor());
         let result = client.health_check(grpc_request).await;
 
         match result {
@@ -441,9 +918,12 @@ impl VmClient {
             strict_validation: true,
         };
 
-        let mut client = self.client.clone();
-        let response = client
-            .validate_bytecode(grpc_request)
+        let response = self
+            .pool
+            .call(Idempotency::NonIdempotent, |channel| {
+                let request = grpc_request.clone();
+                async move { vm_client(channel, self.pool.interceptor()).validate_bytecode(request).await }
+            })
             .await
             .map_err(|e| {
                 error!("gRPC validate_bytecode call failed: {}", e);
@@ -464,3 +944,10 @@ impl VmClient {
         })
     }
 }
+
+fn abi_field_type(abi_type: proto::AbiType) -> AbiFieldType {
+    AbiFieldType {
+        name: abi_type.type_name,
+        params: abi_type.generic_params.into_iter().map(abi_field_type).collect(),
+    }
+}
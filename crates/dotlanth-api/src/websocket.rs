@@ -19,7 +19,9 @@
 use crate::auth::{AuthService, Claims, extract_token_from_header};
 use crate::error::{ApiError, ApiResult};
 use crate::models::{DotEvent, WebSocketMessage};
+use crate::shutdown::Shutdown;
 use crate::vm::VmClient;
+use crate::websocket_mux::{ConnectionChannels, MultiplexConfig, Multiplexer, Outbound, ServerFrame, UnsubscribeReason};
 use base64::{Engine as _, engine::general_purpose};
 use dashmap::DashMap;
 use futures::{SinkExt, StreamExt};
@@ -34,10 +36,14 @@ use sha1::{Digest, Sha1};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::sync::{Mutex, broadcast, mpsc};
 use tokio::time::interval;
-use tokio_stream::wrappers::UnboundedReceiverStream;
-use tokio_tungstenite::{accept_async, tungstenite::Message as TungsteniteMessage};
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::Message as TungsteniteMessage;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::protocol::frame::CloseFrame;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
 use tracing::{debug, error, info, trace, warn};
 use uuid::Uuid;
 
@@ -57,6 +63,12 @@ pub struct WebSocketManager {
 
     /// Connection metrics
     metrics: Arc<WebSocketMetrics>,
+
+    /// Shares upstream streams between the multiplexed channels of all connections
+    multiplexer: Arc<Multiplexer>,
+
+    /// Closes open connections when the server stops
+    shutdown: Shutdown,
 }
 
 /// WebSocket connection
@@ -71,7 +83,10 @@ pub struct WebSocketConnection {
     subscriptions: Arc<RwLock<HashSet<String>>>,
 
     /// Connection sender for sending messages
-    sender: mpsc::UnboundedSender<WebSocketMessage>,
+    sender: mpsc::UnboundedSender<Outbound>,
+
+    /// Multiplexed channels opened by the client
+    channels: Arc<ConnectionChannels>,
 
     /// Connection metrics
     metrics: Arc<WebSocketMetrics>,
@@ -108,28 +123,46 @@ pub struct SubscriptionRequest {
 
 impl WebSocketManager {
     /// Create a new WebSocket manager
-    pub fn new(vm_client: VmClient, auth_service: Arc<Mutex<AuthService>>) -> Self {
+    pub fn new(vm_client: VmClient, auth_service: Arc<Mutex<AuthService>>, shutdown: Shutdown) -> Self {
         let metrics = Arc::new(WebSocketMetrics::default());
 
         // Spawn metrics updater task
         let metrics_clone = metrics.clone();
+        let metrics_shutdown = shutdown.clone();
         tokio::spawn(async move {
             let mut interval = interval(Duration::from_secs(10));
             loop {
-                interval.tick().await;
-                metrics_clone.update_metrics();
+                tokio::select! {
+                    _ = interval.tick() => metrics_clone.update_metrics(),
+                    _ = metrics_shutdown.triggered() => break,
+                }
             }
         });
 
+        let multiplexer = Arc::new(Multiplexer::new(Arc::new(vm_client.clone()), MultiplexConfig::default()));
+
         Self {
             connections: Arc::new(DashMap::new()),
             event_broadcasters: Arc::new(DashMap::new()),
             vm_client,
             auth_service,
             metrics,
+            multiplexer,
+            shutdown,
         }
     }
 
+    /// Serve multiplexed channels from `multiplexer` instead of the VM client's streams
+    pub fn with_multiplexer(mut self, multiplexer: Arc<Multiplexer>) -> Self {
+        self.multiplexer = multiplexer;
+        self
+    }
+
+    /// Multiplexer behind the connections' channels
+    pub fn multiplexer(&self) -> &Arc<Multiplexer> {
+        &self.multiplexer
+    }
+
     /// Handle WebSocket upgrade request
     pub async fn handle_websocket_upgrade(&self, mut req: Request<Incoming>) -> Result<Response<Full<Bytes>>, ApiError> {
         debug!("Handling WebSocket upgrade request");
@@ -170,142 +203,139 @@ impl WebSocketManager {
             });
         };
 
-        // Upgrade to WebSocket connection using hyper's upgrade mechanism
-        match hyper::upgrade::on(&mut req).await {
-            Ok(upgraded) => {
-                // Create a new connection
-                let connection_id = Uuid::new_v4().to_string();
-                let (sender, receiver) = mpsc::unbounded_channel();
-
-                let connection = Arc::new(WebSocketConnection {
-                    id: connection_id.clone(),
-                    claims,
-                    subscriptions: Arc::new(RwLock::new(HashSet::new())),
-                    sender,
-                    metrics: self.metrics.clone(),
-                });
+        let key = req.headers().get("sec-websocket-key").and_then(|key| key.to_str().ok()).ok_or_else(|| ApiError::BadRequest {
+            message: "Missing Sec-WebSocket-Key".to_string(),
+        })?;
 
-                // Add connection to manager
-                self.connections.insert(connection_id.clone(), connection.clone());
+        // Calculate the Sec-WebSocket-Accept header value according to RFC 6455
+        const WEBSOCKET_HANDSHAKE_MAGIC: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+        let mut hasher = Sha1::new();
+        hasher.update(format!("{}{}", key, WEBSOCKET_HANDSHAKE_MAGIC).as_bytes());
+        let accept_key = general_purpose::STANDARD.encode(hasher.finalize());
 
-                // Increment active connections
-                self.metrics.increment_active_connections();
-                self.metrics.increment_total_connections();
+        // The upgrade completes only after the 101 response below has been sent
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let upgraded = match hyper::upgrade::on(&mut req).await {
+                Ok(upgraded) => upgraded,
+                Err(e) => {
+                    error!("WebSocket upgrade failed: {}", e);
+                    return;
+                }
+            };
 
-                info!("New WebSocket connection established: {}", connection_id);
+            let ws_stream = WebSocketStream::from_raw_socket(TokioIo::new(upgraded), Role::Server, None).await;
+            if let Err(e) = manager.serve_connection(ws_stream, claims).await {
+                error!("WebSocket connection error: {}", e);
+            }
+        });
 
-                // Spawn task for handling the connection
-                let manager = self.clone();
-                tokio::spawn(async move {
-                    // Wrap the upgraded connection in TokioIo to make it compatible with tokio-tungstenite
-                    let io = TokioIo::new(upgraded);
+        Ok(Response::builder()
+            .status(StatusCode::SWITCHING_PROTOCOLS)
+            .header("Upgrade", "websocket")
+            .header("Connection", "Upgrade")
+            .header("Sec-WebSocket-Accept", accept_key)
+            .body(Full::new(Bytes::new()))?)
+    }
 
-                    // Convert the upgraded connection to a WebSocket
-                    match accept_async(io).await {
-                        Ok(ws_stream) => {
-                            if let Err(e) = manager.handle_websocket_connection(ws_stream, connection, receiver).await {
-                                error!("WebSocket connection error: {}", e);
-                            }
-                        }
-                        Err(e) => {
-                            error!("Failed to accept WebSocket connection: {}", e);
-                        }
-                    }
-                });
+    /// Register a connection for an established WebSocket and serve it until either side closes
+    async fn serve_connection<S>(&self, ws_stream: WebSocketStream<S>, claims: Claims) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let connection_id = Uuid::new_v4().to_string();
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        let connection = Arc::new(WebSocketConnection {
+            id: connection_id.clone(),
+            claims,
+            subscriptions: Arc::new(RwLock::new(HashSet::new())),
+            channels: ConnectionChannels::new(self.multiplexer.clone(), sender.clone()),
+            sender,
+            metrics: self.metrics.clone(),
+        });
 
-                // Return a successful upgrade response with proper WebSocket headers
-                let key = if let Some(key) = req.headers().get("sec-websocket-key") {
-                    key.to_str().unwrap_or_default()
-                } else {
-                    ""
-                };
+        self.connections.insert(connection_id.clone(), connection.clone());
+        self.metrics.increment_active_connections();
+        self.metrics.increment_total_connections();
 
-                // Calculate the Sec-WebSocket-Accept header value according to RFC 6455
-                // This is required for proper WebSocket handshake completion
-                let accept_key = if !key.is_empty() {
-                    const WEBSOCKET_HANDSHAKE_MAGIC: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
-                    let concat = format!("{}{}", key, WEBSOCKET_HANDSHAKE_MAGIC);
-                    let mut hasher = Sha1::new();
-                    hasher.update(concat.as_bytes());
-                    let result = hasher.finalize();
-                    general_purpose::STANDARD.encode(&result)
-                } else {
-                    String::new()
-                };
+        info!("New WebSocket connection established: {}", connection_id);
 
-                Ok(Response::builder()
-                    .status(StatusCode::SWITCHING_PROTOCOLS)
-                    .header("Upgrade", "websocket")
-                    .header("Connection", "Upgrade")
-                    .header("Sec-WebSocket-Accept", accept_key)
-                    .body(Full::new(Bytes::new()))
-                    .unwrap())
-            }
-            Err(e) => {
-                error!("WebSocket upgrade failed: {}", e);
-                Err(ApiError::InternalServerError {
-                    message: format!("WebSocket upgrade failed: {}", e),
-                })
-            }
-        }
+        let result = self.handle_websocket_connection(ws_stream, connection, receiver).await;
+        self.remove_connection(&connection_id).await;
+        result
     }
 
     /// Handle WebSocket connection
-    async fn handle_websocket_connection(
+    ///
+    /// Incoming frames and queued messages are handled in turn on this task. While
+    /// the socket is slow, channel messages wait in the queue, which is what the
+    /// per-channel buffers count.
+    async fn handle_websocket_connection<S>(
         &self,
-        ws_stream: tokio_tungstenite::WebSocketStream<TokioIo<hyper::upgrade::Upgraded>>,
+        ws_stream: WebSocketStream<S>,
         connection: Arc<WebSocketConnection>,
-        receiver: mpsc::UnboundedReceiver<WebSocketMessage>,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        mut receiver: mpsc::UnboundedReceiver<Outbound>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
         let connection_id = connection.id.clone();
+        let (mut ws_sink, mut ws_stream) = ws_stream.split();
 
-        let (ws_sink, ws_stream) = ws_stream.split();
+        loop {
+            tokio::select! {
+                incoming = ws_stream.next() => match incoming {
+                    Some(Ok(msg)) if msg.is_text() || msg.is_binary() => {
+                        self.metrics.increment_messages_received();
 
-        // Handle incoming messages from the WebSocket
-        let manager = self.clone();
-        let connection_clone = connection.clone();
-        let mut ws_stream = ws_stream;
-        tokio::spawn(async move {
-            while let Some(msg) = ws_stream.next().await {
-                match msg {
-                    Ok(msg) => {
-                        if msg.is_text() || msg.is_binary() {
-                            manager.metrics.increment_messages_received();
-
-                            // Handle the message
-                            if let Err(e) = manager.handle_message(&connection_clone, msg).await {
-                                error!("Error handling WebSocket message: {}", e);
-                            }
-                        } else if msg.is_close() {
-                            info!("WebSocket connection closed: {}", connection_clone.id);
-                            break;
+                        if let Err(e) = self.handle_message(&connection, msg).await {
+                            error!("Error handling WebSocket message: {}", e);
                         }
                     }
-                    Err(e) => {
-                        error!("WebSocket error for connection {}: {}", connection_clone.id, e);
-                        manager.metrics.increment_connection_errors();
+                    Some(Ok(msg)) if msg.is_close() => {
+                        info!("WebSocket connection closed: {}", connection_id);
                         break;
                     }
-                }
-            }
-
-            // Clean up connection
-            manager.remove_connection(&connection_clone.id).await;
-        });
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => {
+                        error!("WebSocket error for connection {}: {}", connection_id, e);
+                        self.metrics.increment_connection_errors();
+                        break;
+                    }
+                    None => break,
+                },
+                outbound = receiver.recv() => {
+                    let Some(outbound) = outbound else {
+                        break;
+                    };
+                    let ws_msg = TungsteniteMessage::Text(outbound.to_text()?);
 
-        // Handle outgoing messages to the WebSocket
-        let mut ws_sink = ws_sink;
-        let mut receiver_stream = UnboundedReceiverStream::new(receiver);
-        while let Some(message) = receiver_stream.next().await {
-            let json_msg = serde_json::to_string(&message)?;
-            let ws_msg = TungsteniteMessage::Text(json_msg);
+                    if let Err(e) = ws_sink.send(ws_msg).await {
+                        error!("Failed to send message to WebSocket connection {}: {}", connection_id, e);
+                        break;
+                    }
+                    outbound.written();
 
-            if let Err(e) = ws_sink.send(ws_msg).await {
-                error!("Failed to send message to WebSocket connection {}: {}", connection_id, e);
-                break;
+                    self.metrics.increment_messages_sent();
+                }
+                _ = self.shutdown.triggered() => {
+                    // Tell the client which channels are going away before closing the socket
+                    for channel in connection.channels.close_all() {
+                        let frame = ServerFrame::Unsubscribed {
+                            channel,
+                            reason: UnsubscribeReason::Shutdown,
+                        };
+                        if ws_sink.send(TungsteniteMessage::Text(serde_json::to_string(&frame)?)).await.is_err() {
+                            break;
+                        }
+                    }
+                    if let Err(e) = ws_sink.send(shutdown_close_frame()).await {
+                        debug!("Failed to send close frame to WebSocket connection {}: {}", connection_id, e);
+                    }
+                    break;
+                }
             }
-
-            self.metrics.increment_messages_sent();
         }
 
         Ok(())
@@ -318,6 +348,12 @@ impl WebSocketManager {
         // Parse the message as JSON
         let text = msg.to_text()?;
 
+        // Multiplexing frames are tagged with `op`
+        if serde_json::from_str::<serde_json::Value>(text).is_ok_and(|value| value.get("op").is_some()) {
+            connection.channels.handle_text(text, &connection.claims);
+            return Ok(());
+        }
+
         // Try to parse as subscription request first
         if let Ok(subscription_req) = serde_json::from_str::<SubscriptionRequest>(text) {
             self.handle_subscription_request(connection, subscription_req).await?;
@@ -340,7 +376,7 @@ impl WebSocketManager {
             timestamp: chrono::Utc::now(),
         };
 
-        connection.sender.send(error_msg)?;
+        connection.send(error_msg)?;
 
         Ok(())
     }
@@ -371,7 +407,7 @@ impl WebSocketManager {
             timestamp: chrono::Utc::now(),
         };
 
-        connection.sender.send(response)?;
+        connection.send(response)?;
 
         Ok(())
     }
@@ -389,7 +425,7 @@ impl WebSocketManager {
                     timestamp: chrono::Utc::now(),
                 };
 
-                connection.sender.send(pong_msg)?;
+                connection.send(pong_msg)?;
             }
             "interactive_execution_request" => {
                 // Handle interactive execution request
@@ -410,7 +446,7 @@ impl WebSocketManager {
                     timestamp: chrono::Utc::now(),
                 };
 
-                connection.sender.send(error_msg)?;
+                connection.send(error_msg)?;
             }
         }
 
@@ -443,7 +479,7 @@ impl WebSocketManager {
             timestamp: chrono::Utc::now(),
         };
 
-        connection.sender.send(response)?;
+        connection.send(response)?;
 
         Ok(())
     }
@@ -474,7 +510,7 @@ impl WebSocketManager {
             timestamp: chrono::Utc::now(),
         };
 
-        connection.sender.send(response)?;
+        connection.send(response)?;
 
         Ok(())
     }
@@ -508,13 +544,41 @@ impl WebSocketManager {
     async fn remove_connection(&self, connection_id: &str) {
         info!("Removing WebSocket connection: {}", connection_id);
 
-        // Remove from connections map
-        self.connections.remove(connection_id);
+        // Remove from connections map, releasing the upstream streams its channels held
+        if let Some((_, connection)) = self.connections.remove(connection_id) {
+            connection.channels.close_all();
+        }
 
         // Decrement active connections
         self.metrics.decrement_active_connections();
     }
 
+    /// Record a connection served outside the manager, such as GraphQL over WebSocket
+    pub(crate) fn connection_opened(&self) {
+        self.metrics.increment_active_connections();
+        self.metrics.increment_total_connections();
+    }
+
+    /// Record the end of a connection registered with `connection_opened`
+    pub(crate) fn connection_closed(&self) {
+        self.metrics.decrement_active_connections();
+    }
+
+    /// Record a frame received on an externally served connection
+    pub(crate) fn message_received(&self) {
+        self.metrics.increment_messages_received();
+    }
+
+    /// Record a frame sent on an externally served connection
+    pub(crate) fn message_sent(&self) {
+        self.metrics.increment_messages_sent();
+    }
+
+    /// Shutdown handle shared with connections served outside the manager
+    pub(crate) fn shutdown(&self) -> &Shutdown {
+        &self.shutdown
+    }
+
     /// Get connection count
     pub fn connection_count(&self) -> usize {
         self.connections.len()
@@ -537,6 +601,16 @@ impl WebSocketConnection {
     pub fn is_subscribed(&self, event_type: &str) -> bool {
         self.subscriptions.read().contains(event_type)
     }
+
+    /// Number of multiplexed channels open on the connection
+    pub fn channel_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Queue a message for the connection's socket
+    fn send(&self, message: WebSocketMessage) -> Result<(), mpsc::error::SendError<Outbound>> {
+        self.sender.send(Outbound::Message(message))
+    }
 }
 
 impl WebSocketMetrics {
@@ -607,6 +681,16 @@ impl Clone for WebSocketManager {
             vm_client: self.vm_client.clone(),
             auth_service: self.auth_service.clone(),
             metrics: self.metrics.clone(),
+            multiplexer: self.multiplexer.clone(),
+            shutdown: self.shutdown.clone(),
         }
     }
 }
+
+/// Close frame telling clients the server is going away and they should reconnect elsewhere
+pub(crate) fn shutdown_close_frame() -> TungsteniteMessage {
+    TungsteniteMessage::Close(Some(CloseFrame {
+        code: CloseCode::Away,
+        reason: "Server shutting down".into(),
+    }))
+}
@@ -0,0 +1,688 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Multiplexed subscriptions over a single WebSocket connection
+//!
+//! Clients of `/api/v1/ws` open channels, each subscribed to one topic, and
+//! receive the messages of all their channels over the same socket. The gateway
+//! keeps one upstream gRPC stream per topic and fans it out to every channel
+//! subscribed to that topic, so upstream calls do not grow with subscribers.
+//!
+//! # Frame format
+//!
+//! Frames are JSON text messages tagged with `op`. They share the connection with
+//! the older `type`-tagged messages such as `ping`.
+//!
+//! Client to server:
+//!
+//! ```json
+//! {"op": "subscribe", "channel": "dot-1", "topic": "dot_events", "dot_id": "abc", "event_types": ["state_changed"]}
+//! {"op": "subscribe", "channel": "paid", "topic": "dot_events", "payload_filters": [{"field": "order.status", "equals": "paid"}]}
+//! {"op": "subscribe", "channel": "cpu", "topic": "vm_metrics", "metric_names": ["cpu_usage"]}
+//! {"op": "subscribe", "channel": "abc-logs", "topic": "logs", "dot_id": "abc"}
+//! {"op": "unsubscribe", "channel": "dot-1"}
+//! ```
+//!
+//! Channel ids are chosen by the client and must be unique among its open
+//! channels. Filters are optional: a `dot_events` channel without `dot_id`
+//! receives the events of every dot, and `payload_filters` keep only events whose
+//! JSON payload has each value at its dotted field path. The `logs` topic carries
+//! dot events of type `log`.
+//!
+//! Server to client:
+//!
+//! ```json
+//! {"op": "subscribed", "channel": "dot-1", "topic": "dot_events", "dot_id": "abc", "event_types": ["state_changed"]}
+//! {"op": "message", "channel": "dot-1", "seq": 1, "data": {"event_id": "...", "dot_id": "abc", "type": "state_changed", ...}}
+//! {"op": "unsubscribed", "channel": "dot-1", "reason": "client"}
+//! {"op": "error", "channel": "dot-1", "code": "duplicate_channel", "message": "..."}
+//! ```
+//!
+//! - `seq` numbers the messages of a channel from 1, so gaps are detectable.
+//! - `data` is a dot event or VM metric as the REST API represents it.
+//! - `reason` is `client` after an unsubscribe, `slow_consumer` when the channel
+//!   fell `channel_buffer` messages behind the socket and was evicted, or
+//!   `shutdown` when the server stops.
+//! - `code` is one of `invalid_frame`, `forbidden`, `subscription_limit`,
+//!   `duplicate_channel` or `unknown_channel`. `channel` is omitted for frames
+//!   that could not be parsed.
+//!
+//! Subscribing requires the `execute:dots` permission. Closing the socket
+//! unsubscribes all of its channels, and an upstream stream is closed once its
+//! last channel is gone.
+
+use crate::auth::Claims;
+use crate::error::ApiResult;
+use crate::grpc_pool::parse_env;
+use crate::models::{DotEvent, PayloadFilter, VmMetric, WebSocketMessage};
+use crate::vm::VmClient;
+use async_trait::async_trait;
+use futures::StreamExt;
+use futures::stream::BoxStream;
+use metrics::counter;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Dot event type carried by the `logs` topic
+pub const LOG_EVENT_TYPE: &str = "log";
+
+/// Permission required to open a channel
+const SUBSCRIBE_PERMISSION: &str = "execute:dots";
+
+/// Delay before reopening an upstream stream that failed or ended
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(5);
+
+/// Sampling interval requested for the VM metrics upstream
+const METRICS_INTERVAL_SECS: u32 = 5;
+
+/// Upstream items buffered for channels that have not picked them up yet
+const UPSTREAM_BUFFER: usize = 1024;
+
+/// Multiplexing limits
+#[derive(Debug, Clone)]
+pub struct MultiplexConfig {
+    /// Channels a single connection may have open at once
+    pub max_channels_per_connection: usize,
+
+    /// Messages a channel may have waiting for the socket before it is evicted
+    pub channel_buffer: usize,
+}
+
+impl Default for MultiplexConfig {
+    fn default() -> Self {
+        Self {
+            max_channels_per_connection: 32,
+            channel_buffer: 256,
+        }
+    }
+}
+
+impl MultiplexConfig {
+    /// Load settings from `DOTLANTH_WS_MAX_CHANNELS` and `DOTLANTH_WS_CHANNEL_BUFFER`
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Some(max_channels) = parse_env::<usize>("DOTLANTH_WS_MAX_CHANNELS").filter(|&max_channels| max_channels > 0) {
+            config.max_channels_per_connection = max_channels;
+        }
+        if let Some(buffer) = parse_env::<usize>("DOTLANTH_WS_CHANNEL_BUFFER").filter(|&buffer| buffer > 0) {
+            config.channel_buffer = buffer;
+        }
+
+        config
+    }
+}
+
+/// What a channel subscribes to, with its filters
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "topic", rename_all = "snake_case")]
+pub enum Topic {
+    DotEvents {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        dot_id: Option<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        event_types: Vec<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        payload_filters: Vec<PayloadFilter>,
+    },
+    VmMetrics {
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        metric_names: Vec<String>,
+    },
+    Logs {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        dot_id: Option<String>,
+    },
+}
+
+impl Topic {
+    /// Upstream stream the topic is served from
+    pub fn upstream(&self) -> UpstreamTopic {
+        match self {
+            Topic::DotEvents { .. } => UpstreamTopic::DotEvents,
+            Topic::VmMetrics { .. } => UpstreamTopic::VmMetrics,
+            Topic::Logs { .. } => UpstreamTopic::Logs,
+        }
+    }
+
+    /// Whether an upstream item passes the channel's filters
+    pub fn matches(&self, item: &UpstreamItem) -> bool {
+        match (self, item) {
+            (Topic::DotEvents { dot_id, event_types, payload_filters }, UpstreamItem::DotEvent(event)) => {
+                dot_id.as_ref().is_none_or(|id| *id == event.dot_id)
+                    && (event_types.is_empty() || event_types.contains(&event.event_type))
+                    && payload_filters.iter().all(|filter| filter.matches(&event.data))
+            }
+            (Topic::Logs { dot_id }, UpstreamItem::DotEvent(event)) => event.event_type == LOG_EVENT_TYPE && dot_id.as_ref().is_none_or(|id| *id == event.dot_id),
+            (Topic::VmMetrics { metric_names }, UpstreamItem::VmMetric(metric)) => metric_names.is_empty() || metric_names.contains(&metric.name),
+            _ => false,
+        }
+    }
+}
+
+/// Frame sent by the client
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ClientFrame {
+    Subscribe {
+        channel: String,
+        #[serde(flatten)]
+        topic: Topic,
+    },
+    Unsubscribe {
+        channel: String,
+    },
+}
+
+/// Frame sent to the client
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ServerFrame {
+    Subscribed {
+        channel: String,
+        #[serde(flatten)]
+        topic: Topic,
+    },
+    Message {
+        channel: String,
+        seq: u64,
+        data: serde_json::Value,
+    },
+    Unsubscribed {
+        channel: String,
+        reason: UnsubscribeReason,
+    },
+    Error {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        channel: Option<String>,
+        code: FrameErrorCode,
+        message: String,
+    },
+}
+
+impl ServerFrame {
+    fn error(channel: Option<String>, code: FrameErrorCode, message: impl Into<String>) -> Self {
+        ServerFrame::Error {
+            channel,
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Why a channel was closed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnsubscribeReason {
+    Client,
+    SlowConsumer,
+    Shutdown,
+}
+
+/// Why a client frame was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FrameErrorCode {
+    InvalidFrame,
+    Forbidden,
+    SubscriptionLimit,
+    DuplicateChannel,
+    UnknownChannel,
+}
+
+/// Upstream stream shared by all channels of a topic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UpstreamTopic {
+    DotEvents,
+    VmMetrics,
+    Logs,
+}
+
+/// Item received from an upstream stream
+#[derive(Debug, Clone)]
+pub enum UpstreamItem {
+    DotEvent(DotEvent),
+    VmMetric(VmMetric),
+}
+
+impl UpstreamItem {
+    fn to_json(&self) -> serde_json::Value {
+        let value = match self {
+            UpstreamItem::DotEvent(event) => serde_json::to_value(event),
+            UpstreamItem::VmMetric(metric) => serde_json::to_value(metric),
+        };
+        value.unwrap_or(serde_json::Value::Null)
+    }
+}
+
+/// Opens the upstream streams channels are fanned out from
+#[async_trait]
+pub trait UpstreamSource: Send + Sync {
+    async fn open(&self, topic: UpstreamTopic) -> ApiResult<BoxStream<'static, ApiResult<UpstreamItem>>>;
+}
+
+#[async_trait]
+impl UpstreamSource for VmClient {
+    async fn open(&self, topic: UpstreamTopic) -> ApiResult<BoxStream<'static, ApiResult<UpstreamItem>>> {
+        Ok(match topic {
+            UpstreamTopic::DotEvents => self.stream_all_dot_events(vec![]).await?.map(|event| event.map(UpstreamItem::DotEvent)).boxed(),
+            UpstreamTopic::Logs => self
+                .stream_all_dot_events(vec![LOG_EVENT_TYPE.to_string()])
+                .await?
+                .map(|event| event.map(UpstreamItem::DotEvent))
+                .boxed(),
+            UpstreamTopic::VmMetrics => self.stream_vm_metrics(vec![], METRICS_INTERVAL_SECS).await?.map(|metric| metric.map(UpstreamItem::VmMetric)).boxed(),
+        })
+    }
+}
+
+/// Shares upstream streams between the channels of every connection
+pub struct Multiplexer {
+    source: Arc<dyn UpstreamSource>,
+    config: RwLock<MultiplexConfig>,
+    upstreams: Mutex<HashMap<UpstreamTopic, Upstream>>,
+}
+
+/// An open upstream stream and the channels reading it
+struct Upstream {
+    sender: broadcast::Sender<Arc<UpstreamItem>>,
+    subscribers: usize,
+    task: JoinHandle<()>,
+}
+
+/// A channel's handle on an upstream stream, released when dropped
+struct Feed {
+    receiver: broadcast::Receiver<Arc<UpstreamItem>>,
+    topic: UpstreamTopic,
+    multiplexer: Arc<Multiplexer>,
+}
+
+impl Drop for Feed {
+    fn drop(&mut self) {
+        self.multiplexer.detach(self.topic);
+    }
+}
+
+impl Multiplexer {
+    pub fn new(source: Arc<dyn UpstreamSource>, config: MultiplexConfig) -> Self {
+        Self {
+            source,
+            config: RwLock::new(config),
+            upstreams: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> MultiplexConfig {
+        self.config.read().clone()
+    }
+
+    /// Replace the limits; channels already open keep the buffer they were opened with
+    pub fn set_config(&self, config: MultiplexConfig) {
+        *self.config.write() = config;
+    }
+
+    /// Number of upstream streams currently open
+    pub fn active_upstreams(&self) -> usize {
+        self.upstreams.lock().len()
+    }
+
+    fn attach(self: &Arc<Self>, topic: UpstreamTopic) -> Feed {
+        let mut upstreams = self.upstreams.lock();
+        let upstream = upstreams.entry(topic).or_insert_with(|| self.open_upstream(topic));
+        upstream.subscribers += 1;
+        Feed {
+            receiver: upstream.sender.subscribe(),
+            topic,
+            multiplexer: self.clone(),
+        }
+    }
+
+    fn detach(&self, topic: UpstreamTopic) {
+        let mut upstreams = self.upstreams.lock();
+        let Some(upstream) = upstreams.get_mut(&topic) else {
+            return;
+        };
+        upstream.subscribers -= 1;
+        if upstream.subscribers == 0 {
+            // Aborting the task drops the gRPC stream, which cancels the call
+            if let Some(upstream) = upstreams.remove(&topic) {
+                upstream.task.abort();
+            }
+            debug!("Closed {:?} upstream stream, no channels left", topic);
+        }
+    }
+
+    fn open_upstream(&self, topic: UpstreamTopic) -> Upstream {
+        let (sender, _) = broadcast::channel(UPSTREAM_BUFFER);
+        let source = self.source.clone();
+        let items = sender.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                match source.open(topic).await {
+                    Ok(mut stream) => {
+                        info!("Opened {:?} upstream stream for WebSocket channels", topic);
+                        while let Some(item) = stream.next().await {
+                            match item {
+                                // No receivers only means every channel is between messages
+                                Ok(item) => {
+                                    let _ = items.send(Arc::new(item));
+                                }
+                                Err(e) => {
+                                    warn!("{:?} upstream stream failed: {}", topic, e);
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => warn!("Failed to open {:?} upstream stream: {}", topic, e),
+                }
+                tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+            }
+        });
+
+        Upstream { sender, subscribers: 0, task }
+    }
+}
+
+/// Frame waiting to be written to a connection's socket
+#[derive(Debug)]
+pub(crate) enum Outbound {
+    Message(WebSocketMessage),
+    Frame(ServerFrame),
+    /// Channel message, counted against the channel's buffer until written
+    Channel {
+        frame: ServerFrame,
+        pending: Arc<AtomicUsize>,
+    },
+}
+
+impl Outbound {
+    pub(crate) fn to_text(&self) -> serde_json::Result<String> {
+        match self {
+            Outbound::Message(message) => serde_json::to_string(message),
+            Outbound::Frame(frame) | Outbound::Channel { frame, .. } => serde_json::to_string(frame),
+        }
+    }
+
+    /// Release the buffer slot once the frame is on the socket
+    pub(crate) fn written(&self) {
+        if let Outbound::Channel { pending, .. } = self {
+            pending.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+}
+
+/// Channels open on one connection
+pub(crate) struct ConnectionChannels {
+    multiplexer: Arc<Multiplexer>,
+    outbound: mpsc::UnboundedSender<Outbound>,
+    channels: Mutex<HashMap<String, OpenChannel>>,
+    next_id: AtomicU64,
+}
+
+struct OpenChannel {
+    /// Tells a channel apart from a later one reusing its id
+    id: u64,
+    task: JoinHandle<()>,
+}
+
+impl ConnectionChannels {
+    pub(crate) fn new(multiplexer: Arc<Multiplexer>, outbound: mpsc::UnboundedSender<Outbound>) -> Arc<Self> {
+        Arc::new(Self {
+            multiplexer,
+            outbound,
+            channels: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+        })
+    }
+
+    /// Parse and apply a client frame, answering malformed ones with an error frame
+    pub(crate) fn handle_text(self: &Arc<Self>, text: &str, claims: &Claims) {
+        match serde_json::from_str::<ClientFrame>(text) {
+            Ok(ClientFrame::Subscribe { channel, topic }) => self.subscribe(channel, topic, claims),
+            Ok(ClientFrame::Unsubscribe { channel }) => self.unsubscribe(channel),
+            Err(e) => self.send(ServerFrame::error(None, FrameErrorCode::InvalidFrame, e.to_string())),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.channels.lock().len()
+    }
+
+    /// Close every channel and return their ids
+    pub(crate) fn close_all(&self) -> Vec<String> {
+        let closed: Vec<(String, OpenChannel)> = self.channels.lock().drain().collect();
+        closed
+            .into_iter()
+            .map(|(channel, open)| {
+                open.task.abort();
+                channel
+            })
+            .collect()
+    }
+
+    fn subscribe(self: &Arc<Self>, channel: String, topic: Topic, claims: &Claims) {
+        if !claims.has_permission(SUBSCRIBE_PERMISSION) {
+            let message = format!("Subscribing requires the {} permission", SUBSCRIBE_PERMISSION);
+            return self.send(ServerFrame::error(Some(channel), FrameErrorCode::Forbidden, message));
+        }
+
+        let config = self.multiplexer.config();
+        let mut channels = self.channels.lock();
+        if channels.contains_key(&channel) {
+            let message = format!("Channel '{}' is already open", channel);
+            return self.send(ServerFrame::error(Some(channel), FrameErrorCode::DuplicateChannel, message));
+        }
+        if channels.len() >= config.max_channels_per_connection {
+            let message = format!("Subscription limit of {} channels per connection reached", config.max_channels_per_connection);
+            return self.send(ServerFrame::error(Some(channel), FrameErrorCode::SubscriptionLimit, message));
+        }
+
+        debug!("Opening channel '{}' on {:?}", channel, topic);
+        let feed = self.multiplexer.attach(topic.upstream());
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        // Confirm before the forwarder starts so `subscribed` precedes the channel's messages
+        self.send(ServerFrame::Subscribed {
+            channel: channel.clone(),
+            topic: topic.clone(),
+        });
+        let task = tokio::spawn(forward(Arc::downgrade(self), channel.clone(), id, topic, feed, config.channel_buffer));
+        channels.insert(channel, OpenChannel { id, task });
+    }
+
+    fn unsubscribe(&self, channel: String) {
+        let open = self.channels.lock().remove(&channel);
+        match open {
+            Some(open) => {
+                open.task.abort();
+                self.send(ServerFrame::Unsubscribed {
+                    channel,
+                    reason: UnsubscribeReason::Client,
+                });
+            }
+            None => {
+                let message = format!("Channel '{}' is not open", channel);
+                self.send(ServerFrame::error(Some(channel), FrameErrorCode::UnknownChannel, message));
+            }
+        }
+    }
+
+    /// Drop a channel that fell behind, unless it was already closed
+    fn evict(&self, channel: &str, id: u64) {
+        let mut channels = self.channels.lock();
+        if channels.get(channel).is_some_and(|open| open.id == id) {
+            channels.remove(channel);
+            drop(channels);
+            counter!("websocket_channels_evicted", 1);
+            self.send(ServerFrame::Unsubscribed {
+                channel: channel.to_string(),
+                reason: UnsubscribeReason::SlowConsumer,
+            });
+        }
+    }
+
+    fn send(&self, frame: ServerFrame) {
+        // Fails only once the connection's writer is gone
+        let _ = self.outbound.send(Outbound::Frame(frame));
+    }
+}
+
+impl Drop for ConnectionChannels {
+    fn drop(&mut self) {
+        for open in self.channels.get_mut().values() {
+            open.task.abort();
+        }
+    }
+}
+
+/// Copy a channel's matching upstream items to its connection
+///
+/// The channel is evicted when `buffer` of its messages are still waiting for the
+/// socket, or when it falls behind the shared upstream itself.
+async fn forward(channels: Weak<ConnectionChannels>, channel: String, id: u64, topic: Topic, mut feed: Feed, buffer: usize) {
+    let pending = Arc::new(AtomicUsize::new(0));
+    let mut seq = 0;
+    loop {
+        let item = match feed.receiver.recv().await {
+            Ok(item) => item,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Channel '{}' missed {} upstream messages, evicting", channel, skipped);
+                if let Some(channels) = channels.upgrade() {
+                    channels.evict(&channel, id);
+                }
+                return;
+            }
+            Err(RecvError::Closed) => return,
+        };
+        if !topic.matches(&item) {
+            continue;
+        }
+
+        let Some(channels) = channels.upgrade() else {
+            return;
+        };
+        if pending.load(Ordering::Acquire) >= buffer {
+            warn!("Channel '{}' has {} messages waiting for the socket, evicting", channel, buffer);
+            channels.evict(&channel, id);
+            return;
+        }
+
+        seq += 1;
+        pending.fetch_add(1, Ordering::AcqRel);
+        let frame = ServerFrame::Message {
+            channel: channel.clone(),
+            seq,
+            data: item.to_json(),
+        };
+        if channels.outbound.send(Outbound::Channel { frame, pending: pending.clone() }).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn event(dot_id: &str, event_type: &str) -> UpstreamItem {
+        event_with_data(dot_id, event_type, serde_json::Value::Null)
+    }
+
+    fn event_with_data(dot_id: &str, event_type: &str, data: serde_json::Value) -> UpstreamItem {
+        UpstreamItem::DotEvent(DotEvent {
+            event_id: "e1".to_string(),
+            dot_id: dot_id.to_string(),
+            event_type: event_type.to_string(),
+            data,
+            metadata: HashMap::new(),
+        })
+    }
+
+    #[test]
+    fn test_parse_client_frames() {
+        let frame: ClientFrame = serde_json::from_str(r#"{"op":"subscribe","channel":"c1","topic":"dot_events","dot_id":"abc"}"#).unwrap();
+        assert_eq!(
+            frame,
+            ClientFrame::Subscribe {
+                channel: "c1".to_string(),
+                topic: Topic::DotEvents {
+                    dot_id: Some("abc".to_string()),
+                    event_types: vec![],
+                    payload_filters: vec![],
+                },
+            }
+        );
+
+        let frame: ClientFrame = serde_json::from_str(r#"{"op":"unsubscribe","channel":"c1"}"#).unwrap();
+        assert_eq!(frame, ClientFrame::Unsubscribe { channel: "c1".to_string() });
+
+        assert!(serde_json::from_str::<ClientFrame>(r#"{"op":"subscribe","channel":"c1","topic":"weather"}"#).is_err());
+    }
+
+    #[test]
+    fn test_server_frames_carry_channel() {
+        let frame = ServerFrame::Message {
+            channel: "c1".to_string(),
+            seq: 3,
+            data: serde_json::json!({"a": 1}),
+        };
+        assert_eq!(serde_json::to_value(&frame).unwrap(), serde_json::json!({"op": "message", "channel": "c1", "seq": 3, "data": {"a": 1}}));
+
+        let frame = ServerFrame::Unsubscribed {
+            channel: "c1".to_string(),
+            reason: UnsubscribeReason::SlowConsumer,
+        };
+        assert_eq!(
+            serde_json::to_value(&frame).unwrap(),
+            serde_json::json!({"op": "unsubscribed", "channel": "c1", "reason": "slow_consumer"})
+        );
+    }
+
+    #[test]
+    fn test_topic_filters() {
+        let dot = Topic::DotEvents {
+            dot_id: Some("abc".to_string()),
+            event_types: vec!["executed".to_string()],
+            payload_filters: vec![],
+        };
+        assert!(dot.matches(&event("abc", "executed")));
+        assert!(!dot.matches(&event("abc", "deployed")));
+        assert!(!dot.matches(&event("xyz", "executed")));
+
+        let frame: ClientFrame =
+            serde_json::from_str(r#"{"op":"subscribe","channel":"c1","topic":"dot_events","payload_filters":[{"field":"order.status","equals":"paid"},{"field":"total","equals":"10"}]}"#).unwrap();
+        let ClientFrame::Subscribe { topic: paid, .. } = frame else {
+            panic!("expected a subscribe frame");
+        };
+        assert!(paid.matches(&event_with_data("abc", "ordered", serde_json::json!({"order": {"status": "paid"}, "total": 10}))));
+        assert!(!paid.matches(&event_with_data("abc", "ordered", serde_json::json!({"order": {"status": "paid"}, "total": "10"}))));
+        assert!(!paid.matches(&event_with_data("abc", "ordered", serde_json::json!({"order": {"status": "open"}, "total": 10}))));
+        assert!(!paid.matches(&event("abc", "ordered")));
+
+        let logs = Topic::Logs { dot_id: None };
+        assert!(logs.matches(&event("xyz", LOG_EVENT_TYPE)));
+        assert!(!logs.matches(&event("xyz", "executed")));
+    }
+}
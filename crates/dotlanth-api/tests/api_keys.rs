@@ -0,0 +1,227 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! API keys through the REST gateway: scope and dot enforcement, revocation and
+//! the rotate flow's grace period
+
+use dotlanth_api::api_keys::{API_KEY_HEADER, ApiKeyConfig, ApiKeyStore};
+use dotlanth_api::auth::{AuthService, Claims, JwtManager};
+use dotlanth_api::db::DatabaseClient;
+use dotlanth_api::grpc_pool::PoolConfig;
+use dotlanth_api::router::Router;
+use dotlanth_api::shutdown::Shutdown;
+use dotlanth_api::vm::VmClient;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde_json::{Value, json};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+
+const JWT_SECRET: &str = "api-keys-test";
+
+/// How long rotated keys keep working in these tests
+const ROTATION_GRACE: Duration = Duration::from_millis(300);
+
+/// How a test request authenticates
+enum Auth<'a> {
+    /// A bearer token for a principal with this role
+    Token(&'a str),
+    Key(&'a str),
+}
+
+struct Gateway {
+    address: std::net::SocketAddr,
+}
+
+impl Gateway {
+    async fn start() -> Self {
+        let shutdown = Shutdown::new();
+        let auth_service = Arc::new(Mutex::new(AuthService::new(JWT_SECRET)));
+        let db_client = DatabaseClient::new("").unwrap();
+        let vm_client = VmClient::connect_lazy("http://127.0.0.1:1", PoolConfig::default()).unwrap();
+        let config = ApiKeyConfig {
+            rotation_grace: ROTATION_GRACE,
+            ..Default::default()
+        };
+        let api_keys = Arc::new(ApiKeyStore::new(config, db_client.clone()));
+        let router = Arc::new(Router::new(auth_service, db_client, vm_client, shutdown).await.unwrap().with_api_keys(api_keys));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let router = router.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |req| {
+                        let router = router.clone();
+                        async move {
+                            let response = match router.route(req).await {
+                                Ok(response) => response,
+                                Err(e) => Response::from(e).map(BodyExt::boxed_unsync),
+                            };
+                            Ok::<_, Infallible>(response)
+                        }
+                    });
+                    let _ = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await;
+                });
+            }
+        });
+
+        Self { address }
+    }
+
+    async fn request(&self, auth: Auth<'_>, method: Method, path: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let stream = TcpStream::connect(self.address).await.unwrap();
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await.unwrap();
+        tokio::spawn(connection);
+
+        let request = Request::builder()
+            .method(method)
+            .uri(path)
+            .header("host", self.address.to_string())
+            .header("content-type", "application/json");
+        let request = match auth {
+            Auth::Token(role) => request.header("authorization", format!("Bearer {}", token_for(role))),
+            Auth::Key(secret) => request.header(API_KEY_HEADER, secret),
+        };
+        let body = body.map(|body| Bytes::from(body.to_string())).unwrap_or_default();
+        let response = sender.send_request(request.body(Full::new(body)).unwrap()).await.unwrap();
+
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let body = if bytes.is_empty() { Value::Null } else { serde_json::from_slice(&bytes).unwrap() };
+        (status, body)
+    }
+
+    /// Create a key as an admin, returning its id and secret
+    async fn create_key(&self, name: &str, scopes: &[&str], dots: Option<&[&str]>) -> (String, String) {
+        let (status, body) = self
+            .request(
+                Auth::Token("admin"),
+                Method::POST,
+                "/api/v1/admin/api-keys",
+                Some(json!({"name": name, "scopes": scopes, "dots": dots})),
+            )
+            .await;
+        assert_eq!(status, StatusCode::CREATED, "{body}");
+        (body["key"]["id"].as_str().unwrap().to_string(), body["secret"].as_str().unwrap().to_string())
+    }
+}
+
+fn token_for(role: &str) -> String {
+    let claims = Claims::new(role.to_string(), vec![role.to_string()], Vec::new(), chrono::Duration::hours(1));
+    JwtManager::new(JWT_SECRET).create_token(&claims).unwrap()
+}
+
+#[tokio::test]
+async fn keys_are_limited_to_their_scopes_and_dots() {
+    let gateway = Gateway::start().await;
+    let (_, reader) = gateway.create_key("reader", &["read"], None).await;
+    let (_, runner) = gateway.create_key("runner", &["execute"], Some(&["dot-a"])).await;
+    let (_, admin) = gateway.create_key("ops", &["admin"], None).await;
+
+    // Only admins manage keys
+    let (status, _) = gateway.request(Auth::Token("user"), Method::GET, "/api/v1/admin/api-keys", None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = gateway.request(Auth::Key(&reader), Method::GET, "/api/v1/collections", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = gateway
+        .request(Auth::Key(&reader), Method::POST, "/api/v1/vm/dots/dot-a/execute", Some(json!({"function": "main", "arguments": []})))
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = gateway.request(Auth::Key(&reader), Method::GET, "/api/v1/admin/api-keys", None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // The runner passes authorization for its own dot only; the runtime is unreachable here
+    let (status, _) = gateway
+        .request(Auth::Key(&runner), Method::POST, "/api/v1/vm/dots/dot-a/execute", Some(json!({"function": "main", "arguments": []})))
+        .await;
+    assert!(status != StatusCode::FORBIDDEN && status != StatusCode::UNAUTHORIZED, "{status}");
+    let (status, _) = gateway
+        .request(Auth::Key(&runner), Method::POST, "/api/v1/vm/dots/dot-b/execute", Some(json!({"function": "main", "arguments": []})))
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = gateway.request(Auth::Key(&runner), Method::GET, "/api/v1/collections", None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = gateway.request(Auth::Key("dlk_not-a-key"), Method::GET, "/api/v1/collections", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // Listing shows prefixes and last use, never secrets
+    let (status, body) = gateway.request(Auth::Key(&admin), Method::GET, "/api/v1/admin/api-keys", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let keys = body.as_array().unwrap();
+    assert_eq!(keys.len(), 3);
+    assert_eq!(keys[0]["name"], "reader");
+    assert!(reader.starts_with(keys[0]["prefix"].as_str().unwrap()));
+    assert!(keys[0]["last_used_at"].is_string());
+    assert!(!body.to_string().contains(&reader));
+}
+
+#[tokio::test]
+async fn revoked_keys_are_rejected() {
+    let gateway = Gateway::start().await;
+    let (id, secret) = gateway.create_key("ci", &["read"], None).await;
+    let (status, _) = gateway.request(Auth::Key(&secret), Method::GET, "/api/v1/collections", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = gateway.request(Auth::Token("admin"), Method::DELETE, &format!("/api/v1/admin/api-keys/{id}"), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["revoked_at"].is_string());
+
+    let (status, body) = gateway.request(Auth::Key(&secret), Method::GET, "/api/v1/collections", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(body["detail"].as_str().unwrap().contains("revoked"), "{body}");
+
+    let (status, _) = gateway.request(Auth::Token("admin"), Method::DELETE, "/api/v1/admin/api-keys/unknown", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn rotated_keys_keep_working_for_the_grace_period() {
+    let gateway = Gateway::start().await;
+    let (id, old) = gateway.create_key("deployer", &["read", "deploy"], None).await;
+
+    let (status, body) = gateway.request(Auth::Token("admin"), Method::POST, &format!("/api/v1/admin/api-keys/{id}/rotate"), None).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let new = body["secret"].as_str().unwrap().to_string();
+    assert_ne!(new, old);
+    assert_eq!(body["key"]["scopes"], json!(["read", "deploy"]));
+
+    for secret in [&old, &new] {
+        let (status, _) = gateway.request(Auth::Key(secret), Method::GET, "/api/v1/collections", None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    // A key is only rotated once
+    let (status, _) = gateway.request(Auth::Token("admin"), Method::POST, &format!("/api/v1/admin/api-keys/{id}/rotate"), None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    tokio::time::sleep(ROTATION_GRACE + Duration::from_millis(100)).await;
+    let (status, body) = gateway.request(Auth::Key(&old), Method::GET, "/api/v1/collections", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(body["detail"].as_str().unwrap().contains("expired"), "{body}");
+    let (status, _) = gateway.request(Auth::Key(&new), Method::GET, "/api/v1/collections", None).await;
+    assert_eq!(status, StatusCode::OK);
+}
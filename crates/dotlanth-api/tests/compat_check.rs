@@ -0,0 +1,162 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The REST contract suite run against an in-process gateway
+
+use dotlanth_api::auth::AuthService;
+use dotlanth_api::compatibility_testing::contract::{ContractCase, ContractStep, capability};
+use dotlanth_api::compatibility_testing::report::CaseStatus;
+use dotlanth_api::compatibility_testing::runner::{ContractRunner, RunnerConfig};
+use dotlanth_api::compatibility_testing::suite::builtin_cases;
+use dotlanth_api::db::DatabaseClient;
+use dotlanth_api::grpc_pool::PoolConfig;
+use dotlanth_api::router::Router;
+use dotlanth_api::shutdown::Shutdown;
+use dotlanth_api::vm::VmClient;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde_json::{Value, json};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+
+/// Start a gateway whose VM is unreachable and return its address
+async fn start_gateway() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let auth_service = Arc::new(Mutex::new(AuthService::new("compat-check-test")));
+    let db_client = DatabaseClient::new("").unwrap();
+    let vm_client = VmClient::connect_lazy("http://127.0.0.1:1", PoolConfig::default()).unwrap();
+    let router = Arc::new(Router::new(auth_service, db_client, vm_client, Shutdown::new()).await.unwrap().with_compat_check(address));
+
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let router = router.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |req| {
+                    let router = router.clone();
+                    async move {
+                        let response = match router.route(req).await {
+                            Ok(response) => response,
+                            Err(e) => Response::from(e).map(BodyExt::boxed_unsync),
+                        };
+                        Ok::<_, Infallible>(response)
+                    }
+                });
+                let _ = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await;
+            });
+        }
+    });
+    address
+}
+
+async fn request(address: SocketAddr, method: Method, path: &str, token: Option<&str>, body: Option<Value>) -> (StatusCode, String) {
+    let stream = TcpStream::connect(address).await.unwrap();
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await.unwrap();
+    tokio::spawn(connection);
+
+    let mut request = Request::builder()
+        .method(method)
+        .uri(path)
+        .header("host", address.to_string())
+        .header("content-type", "application/json");
+    if let Some(token) = token {
+        request = request.header("authorization", format!("Bearer {}", token));
+    }
+    let body = body.map(|body| Bytes::from(body.to_string())).unwrap_or_default();
+    let response = sender.send_request(request.body(Full::new(body)).unwrap()).await.unwrap();
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    (status, String::from_utf8(bytes.to_vec()).unwrap())
+}
+
+async fn login(address: SocketAddr, username: &str) -> String {
+    let (status, body) = request(address, Method::POST, "/api/v1/auth/login", None, Some(json!({"username": username, "password": username}))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    serde_json::from_str::<Value>(&body).unwrap()["access_token"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn test_builtin_suite_passes_without_a_vm() {
+    let address = start_gateway().await;
+    let mut config = RunnerConfig::new(format!("http://{}", address));
+    config.token = Some(login(address, "admin").await);
+    config.capabilities = Some(
+        [capability::AUTH, capability::COLLECTIONS, capability::DOCUMENTS, capability::TRANSACTIONS, capability::DOT_DEPLOYMENT]
+            .map(str::to_string)
+            .to_vec(),
+    );
+
+    let report = ContractRunner::new(config).unwrap().run(&builtin_cases()).await;
+    let failures: Vec<_> = report.cases.iter().filter(|case| case.status == CaseStatus::Failed).map(|case| (&case.id, &case.failures)).collect();
+    assert!(failures.is_empty(), "{:#?}", failures);
+
+    let status = |id: &str| report.cases.iter().find(|case| case.id == id).unwrap().status;
+    assert_eq!(status("documents_paginate"), CaseStatus::Passed);
+    assert_eq!(status("transactions_commit"), CaseStatus::Passed);
+    assert_eq!(status("vm_overview"), CaseStatus::Skipped);
+    assert_eq!(status("abi_input_validation"), CaseStatus::Skipped);
+}
+
+#[tokio::test]
+async fn test_failures_are_reported() {
+    let address = start_gateway().await;
+    let mut config = RunnerConfig::new(format!("http://{}", address));
+    config.capabilities = Some(Vec::new());
+    let cases = vec![
+        ContractCase::new("teapot", "The gateway is a teapot").step(ContractStep::get("/api/v1/ready").anonymous().status(418)),
+        ContractCase::new("unknown_variable", "Undefined variables fail the step").step(ContractStep::get("/api/v1/collections/{nowhere}")),
+        ContractCase::new("ready", "Ready").step(ContractStep::get("/api/v1/ready").anonymous().expect_eq("/status", json!("ready"))),
+    ];
+
+    let report = ContractRunner::new(config).unwrap().run(&cases).await;
+    assert_eq!((report.passed(), report.failed()), (1, 2));
+    assert!(report.cases[0].failures[0].contains("status 200, expected 418"), "{:?}", report.cases[0].failures);
+    assert!(report.cases[1].failures[0].contains("unknown variable {nowhere}"), "{:?}", report.cases[1].failures);
+
+    let xml = report.to_junit_xml();
+    assert!(xml.contains("tests=\"3\" failures=\"2\""), "{}", xml);
+    assert!(xml.contains("<testcase classname=\"contract\" name=\"teapot\""), "{}", xml);
+}
+
+#[tokio::test]
+async fn test_admin_endpoint_runs_a_restricted_check() {
+    let address = start_gateway().await;
+    let admin = login(address, "admin").await;
+
+    let (status, body) = request(address, Method::POST, "/api/v1/admin/compat-check", Some(&admin), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let report: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(report["restricted"], json!(true));
+    let case = |id: &str| report["cases"].as_array().unwrap().iter().find(|case| case["id"] == id).unwrap().clone();
+    assert_eq!(case("documents_round_trip")["status"], "skipped");
+    assert_eq!(case("documents_missing_collection")["status"], "passed");
+
+    let (status, body) = request(address, Method::POST, "/api/v1/admin/compat-check?format=junit", Some(&admin), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.starts_with("<?xml"), "{}", body);
+
+    let user = login(address, "user").await;
+    let (status, _) = request(address, Method::POST, "/api/v1/admin/compat-check", Some(&user), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
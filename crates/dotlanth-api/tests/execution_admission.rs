@@ -0,0 +1,278 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Admission control of dot executions: a load test at five times the runtime's
+//! capacity, and shedding and runtime limits driven through the REST gateway
+
+use dotlanth_api::admission::{AdmissionConfig, AdmissionController, ShedReason};
+use dotlanth_api::auth::{AuthService, Claims, JwtManager};
+use dotlanth_api::db::DatabaseClient;
+use dotlanth_api::grpc_pool::PoolConfig;
+use dotlanth_api::router::Router;
+use dotlanth_api::shutdown::Shutdown;
+use dotlanth_api::vm::VmClient;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::header::HeaderMap;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde_json::{Value, json};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{Mutex, Semaphore};
+
+/// Executions the simulated runtime runs at once
+const CAPACITY: usize = 8;
+
+/// Time the simulated runtime takes per execution
+const SERVICE_TIME: Duration = Duration::from_millis(20);
+
+/// How long clients wait for an execution
+const CLIENT_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Requests sent per tick, five times what the runtime completes in one
+const REQUESTS_PER_TICK: usize = 5 * CAPACITY / 2;
+const TICK: Duration = Duration::from_millis(10);
+const TICKS: usize = 50;
+
+/// Outcome of one simulated request
+struct Sample {
+    sent: Duration,
+    latency: Duration,
+    shed: Option<ShedReason>,
+}
+
+/// Send requests at five times the capacity of a runtime that queues whatever it
+/// cannot run, either straight through or through admission control
+async fn overload(admission: Arc<AdmissionController>) -> Vec<Sample> {
+    let runtime = Arc::new(Semaphore::new(CAPACITY));
+    let start = Instant::now();
+    let mut requests = Vec::new();
+
+    for tick in 0..TICKS {
+        for i in 0..REQUESTS_PER_TICK {
+            let (admission, runtime) = (admission.clone(), runtime.clone());
+            let dot_id = format!("dot-{}", (tick + i) % 4);
+            requests.push(tokio::spawn(async move {
+                let sent = start.elapsed();
+                let shed = match admission.admit(&dot_id, CLIENT_TIMEOUT).await {
+                    Ok(_permit) => {
+                        let _slot = runtime.acquire().await.unwrap();
+                        tokio::time::sleep(SERVICE_TIME).await;
+                        None
+                    }
+                    Err(shed) => Some(shed.reason),
+                };
+                Sample {
+                    sent,
+                    latency: start.elapsed() - sent,
+                    shed,
+                }
+            }));
+        }
+        tokio::time::sleep(TICK).await;
+    }
+
+    let mut samples = Vec::new();
+    for request in requests {
+        samples.push(request.await.unwrap());
+    }
+    samples
+}
+
+fn p99(samples: &[&Sample]) -> Duration {
+    let mut latencies: Vec<_> = samples.iter().map(|sample| sample.latency).collect();
+    latencies.sort();
+    latencies[(latencies.len() * 99 / 100).min(latencies.len() - 1)]
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn p99_stays_bounded_under_five_times_overload() {
+    let admission = Arc::new(AdmissionController::new(AdmissionConfig {
+        enabled: true,
+        max_in_flight: CAPACITY,
+        max_in_flight_per_dot: CAPACITY,
+        max_queued: CAPACITY,
+        default_timeout_ms: CLIENT_TIMEOUT.as_millis() as u64,
+    }));
+    let samples = overload(admission.clone()).await;
+
+    let admitted: Vec<_> = samples.iter().filter(|sample| sample.shed.is_none()).collect();
+    let shed: Vec<_> = samples.iter().filter(|sample| sample.shed.is_some()).collect();
+    let bound = CLIENT_TIMEOUT + SERVICE_TIME;
+
+    // Admitted requests finish within their deadline, early and late in the run alike
+    let halfway = TICK * (TICKS as u32) / 2;
+    let (early, late): (Vec<_>, Vec<_>) = admitted.iter().partition(|sample| sample.sent < halfway);
+    for (phase, samples) in [("early", &early), ("late", &late)] {
+        assert!(!samples.is_empty(), "no {phase} request was admitted");
+        assert!(p99(samples) < bound, "{phase} p99 {:?} exceeds {:?}", p99(samples), bound);
+    }
+
+    // The runtime stays busy while the excess is rejected
+    let run_time = TICK * TICKS as u32;
+    let completable = (run_time.as_millis() / SERVICE_TIME.as_millis()) as usize * CAPACITY;
+    assert!(admitted.len() >= completable / 2, "{} admitted, runtime could complete {}", admitted.len(), completable);
+    assert!(shed.len() >= samples.len() / 2, "{} of {} requests shed", shed.len(), samples.len());
+    assert!(p99(&shed) < bound, "shed p99 {:?}", p99(&shed));
+
+    let counters = admission.counters();
+    assert_eq!(counters.admitted as usize, admitted.len());
+    assert_eq!((counters.running, counters.queued), (0, 0));
+    let by_reason = |reason| shed.iter().filter(|sample| sample.shed == Some(reason)).count() as u64;
+    assert_eq!(counters.shed.concurrency_limit, by_reason(ShedReason::ConcurrencyLimit));
+    assert_eq!(counters.shed.queue_full, by_reason(ShedReason::QueueFull));
+    assert_eq!(counters.shed.deadline_unreachable, by_reason(ShedReason::DeadlineUnreachable));
+    assert!(counters.shed.queue_full > 0);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn unbounded_queueing_grows_latency_under_the_same_load() {
+    let admission = Arc::new(AdmissionController::new(AdmissionConfig { enabled: false, ..Default::default() }));
+    let samples = overload(admission).await;
+
+    // Every request is queued by the runtime, so the last ones wait for all before them
+    let all: Vec<_> = samples.iter().collect();
+    assert!(all.iter().all(|sample| sample.shed.is_none()));
+    assert!(p99(&all) > CLIENT_TIMEOUT * 4, "p99 {:?}", p99(&all));
+}
+
+const JWT_SECRET: &str = "execution-admission-test";
+
+struct Gateway {
+    address: std::net::SocketAddr,
+    router: Arc<Router>,
+}
+
+impl Gateway {
+    async fn start() -> Self {
+        let shutdown = Shutdown::new();
+        let auth_service = Arc::new(Mutex::new(AuthService::new(JWT_SECRET)));
+        let db_client = DatabaseClient::new("").unwrap();
+        let vm_client = VmClient::connect_lazy("http://127.0.0.1:1", PoolConfig::default()).unwrap();
+        let router = Arc::new(Router::new(auth_service, db_client, vm_client, shutdown).await.unwrap());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let serving = router.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let router = serving.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |req| {
+                        let router = router.clone();
+                        async move {
+                            let response = match router.route(req).await {
+                                Ok(response) => response,
+                                Err(e) => Response::from(e).map(BodyExt::boxed_unsync),
+                            };
+                            Ok::<_, Infallible>(response)
+                        }
+                    });
+                    let _ = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await;
+                });
+            }
+        });
+
+        Self { address, router }
+    }
+
+    async fn request(&self, role: &str, method: Method, path: &str, headers: &[(&str, &str)], body: Option<Value>) -> (StatusCode, HeaderMap, Value) {
+        let stream = TcpStream::connect(self.address).await.unwrap();
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await.unwrap();
+        tokio::spawn(connection);
+
+        let mut request = Request::builder()
+            .method(method)
+            .uri(path)
+            .header("host", self.address.to_string())
+            .header("authorization", format!("Bearer {}", token_for(role)))
+            .header("content-type", "application/json");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let body = body.map(|body| Bytes::from(body.to_string())).unwrap_or_default();
+        let response = sender.send_request(request.body(Full::new(body)).unwrap()).await.unwrap();
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let body = if bytes.is_empty() { Value::Null } else { serde_json::from_slice(&bytes).unwrap() };
+        (status, headers, body)
+    }
+}
+
+fn token_for(role: &str) -> String {
+    let claims = Claims::new(role.to_string(), vec![role.to_string()], vec!["execute:dots".to_string()], chrono::Duration::hours(1));
+    JwtManager::new(JWT_SECRET).create_token(&claims).unwrap()
+}
+
+#[tokio::test]
+async fn execute_is_shed_with_retry_after_and_limits_change_at_runtime() {
+    let gateway = Gateway::start().await;
+    let execute =
+        |headers: &'static [(&'static str, &'static str)]| gateway.request("user", Method::POST, "/api/v1/vm/dots/dot-a/execute", headers, Some(json!({"function": "main", "arguments": []})));
+
+    // Only admins read and change the limits
+    let (status, _, _) = gateway
+        .request("user", Method::PUT, "/api/v1/gateway/config", &[], Some(json!({"admission": {"max_in_flight_per_dot": 1}})))
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _, body) = gateway
+        .request("admin", Method::PUT, "/api/v1/gateway/config", &[], Some(json!({"admission": {"max_in_flight": 0}})))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    let (status, _, body) = gateway
+        .request("admin", Method::PUT, "/api/v1/gateway/config", &[], Some(json!({"admission": {"max_in_flight_per_dot": 1}})))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["admission"]["max_in_flight_per_dot"], 1);
+    assert_eq!(body["admission"]["max_in_flight"], AdmissionConfig::default().max_in_flight);
+
+    // With the only slot of dot-a taken, its next execution is rejected at once
+    let admission = gateway.router.admission();
+    let permit = admission.admit("dot-a", CLIENT_TIMEOUT).await.unwrap();
+    let (status, headers, problem) = execute(&[]).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS, "{}", problem);
+    assert_eq!(headers["retry-after"], "1");
+    assert_eq!(problem["reason"], "concurrency_limit");
+
+    let (status, _, _) = execute(&[("x-dotlanth-timeout-ms", "soon")]).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Raising the limit at runtime admits it again
+    let (status, _, _) = gateway
+        .request("admin", Method::PUT, "/api/v1/gateway/config", &[], Some(json!({"admission": {"max_in_flight_per_dot": 2}})))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _, problem) = execute(&[]).await;
+    assert_ne!(status, StatusCode::TOO_MANY_REQUESTS, "{}", problem);
+    drop(permit);
+
+    let (status, _, body) = gateway.request("admin", Method::GET, "/api/v1/gateway/config", &[], None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["admission"]["max_in_flight_per_dot"], 2);
+
+    let (status, _, body) = gateway.request("user", Method::GET, "/api/v1/gateway/admission", &[], None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["counters"]["shed"]["concurrency_limit"], 1);
+    assert_eq!(body["counters"]["running"], 0);
+    assert_eq!(body["counters"]["admitted"], 2);
+}
@@ -0,0 +1,203 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Optimistic concurrency through the REST gateway: document ETags and If-Match
+
+use dotlanth_api::auth::{AuthService, Claims, JwtManager};
+use dotlanth_api::db::DatabaseClient;
+use dotlanth_api::grpc_pool::PoolConfig;
+use dotlanth_api::router::Router;
+use dotlanth_api::shutdown::Shutdown;
+use dotlanth_api::vm::VmClient;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::header::{ETAG, IF_MATCH};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde_json::{Value, json};
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+
+const JWT_SECRET: &str = "rest-document-versions-test";
+
+/// A gateway response: status, ETag header and JSON body
+struct Reply {
+    status: StatusCode,
+    etag: Option<String>,
+    body: Value,
+}
+
+struct Gateway {
+    address: std::net::SocketAddr,
+    token: String,
+}
+
+impl Gateway {
+    async fn start() -> Self {
+        let shutdown = Shutdown::new();
+        let auth_service = Arc::new(Mutex::new(AuthService::new(JWT_SECRET)));
+        let db_client = DatabaseClient::new("").unwrap();
+        let vm_client = VmClient::connect_lazy("http://127.0.0.1:1", PoolConfig::default()).unwrap();
+        let router = Arc::new(Router::new(auth_service, db_client, vm_client, shutdown).await.unwrap());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let router = router.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |req| {
+                        let router = router.clone();
+                        async move {
+                            let response = match router.route(req).await {
+                                Ok(response) => response,
+                                Err(e) => Response::from(e).map(BodyExt::boxed_unsync),
+                            };
+                            Ok::<_, Infallible>(response)
+                        }
+                    });
+                    let _ = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await;
+                });
+            }
+        });
+
+        let gateway = Self { address, token: token_for("alice") };
+        let reply = gateway.request(Method::POST, "/api/v1/collections/orders", None, None).await;
+        assert_eq!(reply.status, StatusCode::CREATED);
+        gateway
+    }
+
+    async fn request(&self, method: Method, path: &str, if_match: Option<&str>, body: Option<Value>) -> Reply {
+        let stream = TcpStream::connect(self.address).await.unwrap();
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await.unwrap();
+        tokio::spawn(connection);
+
+        let mut request = Request::builder()
+            .method(method)
+            .uri(path)
+            .header("host", self.address.to_string())
+            .header("authorization", format!("Bearer {}", self.token))
+            .header("content-type", "application/json");
+        if let Some(if_match) = if_match {
+            request = request.header(IF_MATCH, if_match);
+        }
+        let body = body.map(|body| Bytes::from(body.to_string())).unwrap_or_default();
+        let response = sender.send_request(request.body(Full::new(body)).unwrap()).await.unwrap();
+
+        let status = response.status();
+        let etag = response.headers().get(ETAG).map(|value| value.to_str().unwrap().to_string());
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let body = if bytes.is_empty() { Value::Null } else { serde_json::from_slice(&bytes).unwrap() };
+        Reply { status, etag, body }
+    }
+
+    async fn create(&self, content: Value) -> String {
+        let reply = self.request(Method::POST, "/api/v1/collections/orders/documents", None, Some(json!({"content": content}))).await;
+        assert_eq!(reply.status, StatusCode::CREATED, "{}", reply.body);
+        reply.body["id"].as_str().unwrap().to_string()
+    }
+
+    async fn read(&self, id: &str) -> Reply {
+        self.request(Method::GET, &format!("/api/v1/collections/orders/documents/{}", id), None, None).await
+    }
+
+    async fn update(&self, id: &str, if_match: Option<&str>, content: Value) -> Reply {
+        self.request(Method::PUT, &format!("/api/v1/collections/orders/documents/{}", id), if_match, Some(json!({"content": content})))
+            .await
+    }
+
+    async fn delete(&self, id: &str, if_match: Option<&str>) -> Reply {
+        self.request(Method::DELETE, &format!("/api/v1/collections/orders/documents/{}", id), if_match, None).await
+    }
+}
+
+fn token_for(subject: &str) -> String {
+    let claims = Claims::new(
+        subject.to_string(),
+        vec!["user".to_string()],
+        ["read:documents", "write:documents", "delete:documents"].iter().map(|p| p.to_string()).collect(),
+        chrono::Duration::hours(1),
+    );
+    JwtManager::new(JWT_SECRET).create_token(&claims).unwrap()
+}
+
+/// The ETag for `version`
+fn etag(version: u64) -> String {
+    format!("\"{}\"", version)
+}
+
+#[tokio::test]
+async fn reads_and_writes_carry_the_document_version_as_etag() {
+    let gateway = Gateway::start().await;
+    let id = gateway.create(json!({"status": "new"})).await;
+
+    let read = gateway.read(&id).await;
+    assert_eq!(read.status, StatusCode::OK);
+    let version = read.body["version"].as_u64().unwrap();
+    assert_eq!(read.etag, Some(etag(version)));
+
+    let updated = gateway.update(&id, Some(&etag(version)), json!({"status": "paid"})).await;
+    assert_eq!(updated.status, StatusCode::OK, "{}", updated.body);
+    assert_eq!(updated.etag, Some(etag(version + 1)));
+    assert_eq!(updated.body["version"], version + 1);
+
+    // Unconditional writes still bump the version
+    assert_eq!(gateway.update(&id, None, json!({"status": "shipped"})).await.etag, Some(etag(version + 2)));
+    assert_eq!(gateway.update(&id, Some("*"), json!({"status": "delivered"})).await.etag, Some(etag(version + 3)));
+    assert_eq!(gateway.read(&id).await.body["content"], json!({"status": "delivered"}));
+}
+
+#[tokio::test]
+async fn stale_if_match_is_rejected_with_the_current_version() {
+    let gateway = Gateway::start().await;
+    let id = gateway.create(json!({"status": "new"})).await;
+    let version = gateway.read(&id).await.body["version"].as_u64().unwrap();
+    assert_eq!(gateway.update(&id, Some(&etag(version)), json!({"status": "paid"})).await.status, StatusCode::OK);
+
+    // A second writer still holding the first version loses
+    let stale = gateway.update(&id, Some(&etag(version)), json!({"status": "cancelled"})).await;
+    assert_eq!(stale.status, StatusCode::PRECONDITION_FAILED, "{}", stale.body);
+    assert_eq!(stale.etag, Some(etag(version + 1)));
+    assert_eq!(stale.body["type"], "https://api.dotlanth.com/problems/version_conflict");
+    assert_eq!(stale.body["current_version"], version + 1);
+    assert_eq!(gateway.read(&id).await.body["content"], json!({"status": "paid"}));
+
+    let stale = gateway.delete(&id, Some(&etag(version))).await;
+    assert_eq!(stale.status, StatusCode::PRECONDITION_FAILED, "{}", stale.body);
+    assert_eq!(gateway.read(&id).await.status, StatusCode::OK);
+
+    assert_eq!(gateway.delete(&id, Some(&etag(version + 1))).await.status, StatusCode::NO_CONTENT);
+    assert_eq!(gateway.read(&id).await.status, StatusCode::NOT_FOUND);
+    assert_eq!(gateway.delete(&id, Some(&etag(version + 1))).await.status, StatusCode::NOT_FOUND);
+    assert_eq!(gateway.update(&id, Some(&etag(version + 1)), json!({})).await.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn malformed_if_match_is_a_bad_request() {
+    let gateway = Gateway::start().await;
+    let id = gateway.create(json!({"status": "new"})).await;
+    let version = gateway.read(&id).await.body["version"].clone();
+
+    for header in ["1", "W/\"1\"", "\"one\""] {
+        let reply = gateway.update(&id, Some(header), json!({"status": "paid"})).await;
+        assert_eq!(reply.status, StatusCode::BAD_REQUEST, "{}: {}", header, reply.body);
+    }
+    assert_eq!(gateway.read(&id).await.body["version"], version);
+}
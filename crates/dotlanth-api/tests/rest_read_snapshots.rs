@@ -0,0 +1,195 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Read snapshots through the REST gateway: one token reads a single point in time
+//! across requests and collections
+
+use dotlanth_api::auth::{AuthService, Claims, JwtManager};
+use dotlanth_api::db::DatabaseClient;
+use dotlanth_api::grpc_pool::PoolConfig;
+use dotlanth_api::router::Router;
+use dotlanth_api::shutdown::Shutdown;
+use dotlanth_api::vm::VmClient;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::header::ETAG;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde_json::{Value, json};
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+
+const JWT_SECRET: &str = "rest-read-snapshots-test";
+
+/// A gateway response: status, ETag header and JSON body
+struct Reply {
+    status: StatusCode,
+    etag: Option<String>,
+    body: Value,
+}
+
+struct Gateway {
+    address: std::net::SocketAddr,
+}
+
+impl Gateway {
+    async fn start() -> Self {
+        let shutdown = Shutdown::new();
+        let auth_service = Arc::new(Mutex::new(AuthService::new(JWT_SECRET)));
+        let db_client = DatabaseClient::new("").unwrap();
+        let vm_client = VmClient::connect_lazy("http://127.0.0.1:1", PoolConfig::default()).unwrap();
+        let router = Arc::new(Router::new(auth_service, db_client, vm_client, shutdown).await.unwrap());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let router = router.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |req| {
+                        let router = router.clone();
+                        async move {
+                            let response = match router.route(req).await {
+                                Ok(response) => response,
+                                Err(e) => Response::from(e).map(BodyExt::boxed_unsync),
+                            };
+                            Ok::<_, Infallible>(response)
+                        }
+                    });
+                    let _ = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await;
+                });
+            }
+        });
+
+        let gateway = Self { address };
+        for collection in ["customers", "orders"] {
+            let reply = gateway.request("alice", Method::POST, &format!("/api/v1/collections/{}", collection), None).await;
+            assert_eq!(reply.status, StatusCode::CREATED);
+        }
+        gateway
+    }
+
+    async fn request(&self, subject: &str, method: Method, path: &str, body: Option<Value>) -> Reply {
+        let stream = TcpStream::connect(self.address).await.unwrap();
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await.unwrap();
+        tokio::spawn(connection);
+
+        let request = Request::builder()
+            .method(method)
+            .uri(path)
+            .header("host", self.address.to_string())
+            .header("authorization", format!("Bearer {}", token_for(subject)))
+            .header("content-type", "application/json");
+        let body = body.map(|body| Bytes::from(body.to_string())).unwrap_or_default();
+        let response = sender.send_request(request.body(Full::new(body)).unwrap()).await.unwrap();
+
+        let status = response.status();
+        let etag = response.headers().get(ETAG).map(|value| value.to_str().unwrap().to_string());
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let body = if bytes.is_empty() { Value::Null } else { serde_json::from_slice(&bytes).unwrap() };
+        Reply { status, etag, body }
+    }
+
+    async fn create(&self, collection: &str, content: Value) -> String {
+        let path = format!("/api/v1/collections/{}/documents", collection);
+        let reply = self.request("alice", Method::POST, &path, Some(json!({"content": content}))).await;
+        assert_eq!(reply.status, StatusCode::CREATED, "{}", reply.body);
+        reply.body["id"].as_str().unwrap().to_string()
+    }
+
+    async fn begin_snapshot(&self) -> String {
+        let reply = self.request("alice", Method::POST, "/api/v1/snapshots", None).await;
+        assert_eq!(reply.status, StatusCode::CREATED, "{}", reply.body);
+        reply.body["token"].as_str().unwrap().to_string()
+    }
+
+    async fn total_items(&self, collection: &str, query: &str) -> Reply {
+        self.request("alice", Method::GET, &format!("/api/v1/collections/{}/documents{}", collection, query), None).await
+    }
+}
+
+fn token_for(subject: &str) -> String {
+    let claims = Claims::new(
+        subject.to_string(),
+        vec!["user".to_string()],
+        ["read:documents", "write:documents", "delete:documents"].iter().map(|p| p.to_string()).collect(),
+        chrono::Duration::hours(1),
+    );
+    JwtManager::new(JWT_SECRET).create_token(&claims).unwrap()
+}
+
+#[tokio::test]
+async fn reads_through_a_snapshot_ignore_later_writes() {
+    let gateway = Gateway::start().await;
+    let customer = gateway.create("customers", json!({"name": "Ada", "tier": "free"})).await;
+    gateway.create("orders", json!({"customer": "Ada", "total": 10})).await;
+
+    let token = gateway.begin_snapshot().await;
+    let with = format!("?snapshot={}", token);
+    let customer_path = format!("/api/v1/collections/customers/documents/{}", customer);
+    let reply = gateway.request("alice", Method::PUT, &customer_path, Some(json!({"content": {"name": "Ada", "tier": "pro"}}))).await;
+    assert_eq!(reply.status, StatusCode::OK, "{}", reply.body);
+    gateway.create("orders", json!({"customer": "Ada", "total": 32})).await;
+
+    let read = gateway.request("alice", Method::GET, &format!("{}{}", customer_path, with), None).await;
+    assert_eq!(read.status, StatusCode::OK, "{}", read.body);
+    assert_eq!(read.body["content"]["tier"], "free");
+    assert_eq!(read.etag, None);
+    assert_eq!(gateway.request("alice", Method::GET, &customer_path, None).await.body["content"]["tier"], "pro");
+
+    assert_eq!(gateway.total_items("orders", &with).await.body["pagination"]["total_items"], 1);
+    assert_eq!(gateway.total_items("orders", "").await.body["pagination"]["total_items"], 2);
+    let search = gateway
+        .request("alice", Method::GET, &format!("/api/v1/collections/orders/search?q=ada&snapshot={}", token), None)
+        .await;
+    assert_eq!(search.body["total_matches"], 1);
+
+    let spec = json!({"aggregates": [{"function": "sum", "field": "total", "as": "revenue"}]});
+    let totals = gateway.request("alice", Method::POST, &format!("/api/v1/collections/orders/aggregate{}", with), Some(spec)).await;
+    assert_eq!(totals.status, StatusCode::OK, "{}", totals.body);
+    assert_eq!(totals.body["groups"][0]["values"]["revenue"], 10);
+
+    let end = gateway.request("alice", Method::DELETE, &format!("/api/v1/snapshots/{}", token), None).await;
+    assert_eq!(end.status, StatusCode::NO_CONTENT);
+    assert_eq!(gateway.total_items("orders", &with).await.status, StatusCode::NOT_FOUND);
+    let again = gateway.request("alice", Method::DELETE, &format!("/api/v1/snapshots/{}", token), None).await;
+    assert_eq!(again.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn snapshots_belong_to_their_subject() {
+    let gateway = Gateway::start().await;
+    let token = gateway.begin_snapshot().await;
+
+    let path = format!("/api/v1/collections/orders/documents?snapshot={}", token);
+    assert_eq!(gateway.request("mallory", Method::GET, &path, None).await.status, StatusCode::NOT_FOUND);
+    let end = gateway.request("mallory", Method::DELETE, &format!("/api/v1/snapshots/{}", token), None).await;
+    assert_eq!(end.status, StatusCode::NOT_FOUND);
+    assert_eq!(gateway.request("alice", Method::GET, &path, None).await.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn snapshot_and_as_of_are_exclusive() {
+    let gateway = Gateway::start().await;
+    let token = gateway.begin_snapshot().await;
+
+    let reply = gateway.total_items("orders", &format!("?snapshot={}&as_of=2025-06-01T12:00:00Z", token)).await;
+    assert_eq!(reply.status, StatusCode::BAD_REQUEST, "{}", reply.body);
+}
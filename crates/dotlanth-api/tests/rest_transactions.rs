@@ -0,0 +1,313 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Document transactions driven through the REST gateway
+
+use dotlanth_api::auth::{AuthService, Claims, JwtManager};
+use dotlanth_api::db::DatabaseClient;
+use dotlanth_api::grpc_pool::PoolConfig;
+use dotlanth_api::router::Router;
+use dotlanth_api::shutdown::Shutdown;
+use dotlanth_api::transactions::{TRANSACTION_HEADER, TransactionConfig};
+use dotlanth_api::vm::VmClient;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde_json::{Value, json};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+
+const JWT_SECRET: &str = "rest-transactions-test";
+
+struct Gateway {
+    address: std::net::SocketAddr,
+    token: String,
+}
+
+impl Gateway {
+    async fn start(config: TransactionConfig) -> Self {
+        let shutdown = Shutdown::new();
+        let auth_service = Arc::new(Mutex::new(AuthService::new(JWT_SECRET)));
+        let db_client = DatabaseClient::new("").unwrap().with_transactions(config);
+        let vm_client = VmClient::connect_lazy("http://127.0.0.1:1", PoolConfig::default()).unwrap();
+        let router = Arc::new(Router::new(auth_service, db_client, vm_client, shutdown).await.unwrap());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let router = router.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |req| {
+                        let router = router.clone();
+                        async move {
+                            let response = match router.route(req).await {
+                                Ok(response) => response,
+                                Err(e) => Response::from(e).map(BodyExt::boxed_unsync),
+                            };
+                            Ok::<_, Infallible>(response)
+                        }
+                    });
+                    let _ = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await;
+                });
+            }
+        });
+
+        let gateway = Self { address, token: token_for("alice") };
+        let (status, _) = gateway.request(Method::POST, "/api/v1/collections/accounts", None, None).await;
+        assert_eq!(status, StatusCode::CREATED);
+        gateway
+    }
+
+    async fn request(&self, method: Method, path: &str, txn: Option<&str>, body: Option<Value>) -> (StatusCode, Value) {
+        self.request_as(&self.token, method, path, txn, body).await
+    }
+
+    async fn request_as(&self, token: &str, method: Method, path: &str, txn: Option<&str>, body: Option<Value>) -> (StatusCode, Value) {
+        let stream = TcpStream::connect(self.address).await.unwrap();
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await.unwrap();
+        tokio::spawn(connection);
+
+        let mut request = Request::builder()
+            .method(method)
+            .uri(path)
+            .header("host", self.address.to_string())
+            .header("authorization", format!("Bearer {}", token))
+            .header("content-type", "application/json");
+        if let Some(txn) = txn {
+            request = request.header(TRANSACTION_HEADER, txn);
+        }
+        let body = body.map(|body| Bytes::from(body.to_string())).unwrap_or_default();
+        let response = sender.send_request(request.body(Full::new(body)).unwrap()).await.unwrap();
+
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let body = if bytes.is_empty() { Value::Null } else { serde_json::from_slice(&bytes).unwrap() };
+        (status, body)
+    }
+
+    async fn create(&self, txn: Option<&str>, content: Value) -> String {
+        let (status, body) = self.request(Method::POST, "/api/v1/collections/accounts/documents", txn, Some(json!({"content": content}))).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        body["id"].as_str().unwrap().to_string()
+    }
+
+    async fn read(&self, txn: Option<&str>, id: &str) -> (StatusCode, Value) {
+        let (status, body) = self.request(Method::GET, &format!("/api/v1/collections/accounts/documents/{}", id), txn, None).await;
+        (status, body["content"].clone())
+    }
+
+    async fn update(&self, txn: Option<&str>, id: &str, content: Value) -> (StatusCode, Value) {
+        self.request(Method::PUT, &format!("/api/v1/collections/accounts/documents/{}", id), txn, Some(json!({"content": content})))
+            .await
+    }
+
+    async fn begin(&self, isolation: &str) -> String {
+        let (status, body) = self.request(Method::POST, "/api/v1/transactions", None, Some(json!({"isolation": isolation}))).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        assert_eq!(body["isolation"], isolation);
+        body["id"].as_str().unwrap().to_string()
+    }
+
+    async fn commit(&self, txn: &str) -> (StatusCode, Value) {
+        self.request(Method::POST, &format!("/api/v1/transactions/{}/commit", txn), None, None).await
+    }
+}
+
+fn token_for(subject: &str) -> String {
+    let claims = Claims::new(
+        subject.to_string(),
+        vec!["user".to_string()],
+        ["read:documents", "write:documents", "delete:documents"].iter().map(|p| p.to_string()).collect(),
+        chrono::Duration::hours(1),
+    );
+    JwtManager::new(JWT_SECRET).create_token(&claims).unwrap()
+}
+
+/// Assert a transaction was aborted with `code`
+fn assert_aborted(status: StatusCode, problem: &Value, code: &str, retryable: bool) {
+    assert_eq!(status, StatusCode::CONFLICT, "{}", problem);
+    assert_eq!(problem["code"], code, "{}", problem);
+    assert_eq!(problem["retryable"], retryable, "{}", problem);
+}
+
+#[tokio::test]
+async fn commit_applies_every_write_at_once() {
+    let gateway = Gateway::start(TransactionConfig::default()).await;
+    let ids = [
+        gateway.create(None, json!({"balance": 100})).await,
+        gateway.create(None, json!({"balance": 50})).await,
+        gateway.create(None, json!({"balance": 0})).await,
+    ];
+
+    let txn = gateway.begin("serializable").await;
+    for (id, balance) in ids.iter().zip([70, 60, 20]) {
+        let (status, _) = gateway.update(Some(&txn), id, json!({"balance": balance})).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let created = gateway.create(Some(&txn), json!({"balance": 0, "ledger": true})).await;
+
+    // The transaction sees its own writes; nothing is visible outside it yet
+    assert_eq!(gateway.read(Some(&txn), &ids[0]).await, (StatusCode::OK, json!({"balance": 70})));
+    assert_eq!(gateway.read(None, &ids[0]).await, (StatusCode::OK, json!({"balance": 100})));
+    assert_eq!(gateway.read(None, &created).await.0, StatusCode::NOT_FOUND);
+
+    assert_eq!(gateway.commit(&txn).await.0, StatusCode::NO_CONTENT);
+    for (id, balance) in ids.iter().zip([70, 60, 20]) {
+        assert_eq!(gateway.read(None, id).await, (StatusCode::OK, json!({"balance": balance})));
+    }
+    assert_eq!(gateway.read(None, &created).await, (StatusCode::OK, json!({"balance": 0, "ledger": true})));
+
+    // A finished transaction cannot be joined or committed again
+    assert_eq!(gateway.read(Some(&txn), &ids[0]).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(gateway.commit(&txn).await.0, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn abort_discards_writes() {
+    let gateway = Gateway::start(TransactionConfig::default()).await;
+    let id = gateway.create(None, json!({"balance": 100})).await;
+
+    let txn = gateway.begin("read_committed").await;
+    gateway.update(Some(&txn), &id, json!({"balance": 0})).await;
+    let (status, _) = gateway.request(Method::DELETE, &format!("/api/v1/collections/accounts/documents/{}", id), Some(&txn), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let created = gateway.create(Some(&txn), json!({"balance": 1})).await;
+    assert_eq!(gateway.read(Some(&txn), &id).await.0, StatusCode::NOT_FOUND);
+
+    let (status, _) = gateway.request(Method::DELETE, &format!("/api/v1/transactions/{}", txn), None, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    assert_eq!(gateway.read(None, &id).await, (StatusCode::OK, json!({"balance": 100})));
+    assert_eq!(gateway.read(None, &created).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(gateway.commit(&txn).await.0, StatusCode::NOT_FOUND);
+
+    // Its locks went with it
+    let next = gateway.begin("serializable").await;
+    assert_eq!(gateway.update(Some(&next), &id, json!({"balance": 90})).await.0, StatusCode::OK);
+    assert_eq!(gateway.commit(&next).await.0, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn expired_transactions_are_aborted() {
+    let gateway = Gateway::start(TransactionConfig {
+        max_lifetime: Duration::from_millis(300),
+        ..TransactionConfig::default()
+    })
+    .await;
+    let id = gateway.create(None, json!({"balance": 100})).await;
+
+    let txn = gateway.begin("serializable").await;
+    assert_eq!(gateway.update(Some(&txn), &id, json!({"balance": 0})).await.0, StatusCode::OK);
+    tokio::time::sleep(Duration::from_millis(400)).await;
+
+    let (status, problem) = gateway.update(Some(&txn), &id, json!({"balance": 1})).await;
+    assert_aborted(status, &problem, "transaction_expired", true);
+    assert_eq!(gateway.commit(&txn).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(gateway.read(None, &id).await, (StatusCode::OK, json!({"balance": 100})));
+
+    // Committing after the deadline fails the same way
+    let late = gateway.begin("serializable").await;
+    assert_eq!(gateway.update(Some(&late), &id, json!({"balance": 2})).await.0, StatusCode::OK);
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    let (status, problem) = gateway.commit(&late).await;
+    assert_aborted(status, &problem, "transaction_expired", true);
+    assert_eq!(gateway.read(None, &id).await, (StatusCode::OK, json!({"balance": 100})));
+
+    // An expired transaction nobody touches again still releases its locks
+    let abandoned = gateway.begin("serializable").await;
+    assert_eq!(gateway.update(Some(&abandoned), &id, json!({"balance": 3})).await.0, StatusCode::OK);
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    let next = gateway.begin("serializable").await;
+    assert_eq!(gateway.update(Some(&next), &id, json!({"balance": 4})).await.0, StatusCode::OK);
+    assert_eq!(gateway.commit(&next).await.0, StatusCode::NO_CONTENT);
+    assert_eq!(gateway.read(None, &id).await, (StatusCode::OK, json!({"balance": 4})));
+}
+
+#[tokio::test]
+async fn interleaved_conflicts_are_retryable() {
+    let gateway = Gateway::start(TransactionConfig::default()).await;
+    let id = gateway.create(None, json!({"balance": 100})).await;
+
+    // A serializable reader locks the document against a second writer, which is
+    // aborted rather than left waiting
+    let reader = gateway.begin("serializable").await;
+    let writer = gateway.begin("serializable").await;
+    assert_eq!(gateway.read(Some(&reader), &id).await, (StatusCode::OK, json!({"balance": 100})));
+    let (status, problem) = gateway.update(Some(&writer), &id, json!({"balance": 0})).await;
+    assert_aborted(status, &problem, "lock_conflict", true);
+    assert_eq!(gateway.commit(&writer).await.0, StatusCode::NOT_FOUND);
+
+    assert_eq!(gateway.update(Some(&reader), &id, json!({"balance": 80})).await.0, StatusCode::OK);
+    assert_eq!(gateway.commit(&reader).await.0, StatusCode::NO_CONTENT);
+
+    // Two repeatable-read transactions updating the same document: the second commit
+    // would lose the first's update
+    let first = gateway.begin("repeatable_read").await;
+    let second = gateway.begin("repeatable_read").await;
+    assert_eq!(gateway.read(Some(&first), &id).await.1, json!({"balance": 80}));
+    assert_eq!(gateway.read(Some(&second), &id).await.1, json!({"balance": 80}));
+    assert_eq!(gateway.update(Some(&first), &id, json!({"balance": 70})).await.0, StatusCode::OK);
+    assert_eq!(gateway.commit(&first).await.0, StatusCode::NO_CONTENT);
+    assert_eq!(gateway.update(Some(&second), &id, json!({"balance": 60})).await.0, StatusCode::OK);
+    let (status, problem) = gateway.commit(&second).await;
+    assert_aborted(status, &problem, "write_conflict", true);
+
+    // Retrying the lost transaction succeeds
+    let retry = gateway.begin("repeatable_read").await;
+    assert_eq!(gateway.read(Some(&retry), &id).await.1, json!({"balance": 70}));
+    assert_eq!(gateway.update(Some(&retry), &id, json!({"balance": 50})).await.0, StatusCode::OK);
+    assert_eq!(gateway.commit(&retry).await.0, StatusCode::NO_CONTENT);
+    assert_eq!(gateway.read(None, &id).await.1, json!({"balance": 50}));
+}
+
+#[tokio::test]
+async fn transactions_are_private_and_capped() {
+    let gateway = Gateway::start(TransactionConfig {
+        max_operations: 2,
+        ..TransactionConfig::default()
+    })
+    .await;
+    let id = gateway.create(None, json!({"balance": 100})).await;
+
+    let txn = gateway.begin("serializable").await;
+    let (status, _) = gateway
+        .request_as(&token_for("mallory"), Method::GET, &format!("/api/v1/collections/accounts/documents/{}", id), Some(&txn), None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = gateway
+        .request_as(&token_for("mallory"), Method::POST, &format!("/api/v1/transactions/{}/commit", txn), None, None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    assert_eq!(gateway.read(Some(&txn), &id).await.0, StatusCode::OK);
+    assert_eq!(gateway.update(Some(&txn), &id, json!({"balance": 0})).await.0, StatusCode::OK);
+    let (status, problem) = gateway.update(Some(&txn), &id, json!({"balance": 1})).await;
+    assert_aborted(status, &problem, "operation_limit", false);
+    assert_eq!(gateway.commit(&txn).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(gateway.read(None, &id).await.1, json!({"balance": 100}));
+
+    assert_eq!(
+        gateway.request(Method::POST, "/api/v1/transactions/not-a-transaction/commit", None, None).await.0,
+        StatusCode::NOT_FOUND
+    );
+}
@@ -0,0 +1,76 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The gateway's calls to the runtime, checked by the runtime's own authentication
+
+use dotlanth_api::grpc_pool::{ChannelPool, PoolConfig, RuntimeInterceptor};
+use dotvm_runtime::auth::{AccessLevel, Authenticator, MethodPolicy, StaticKeyStore};
+use std::sync::Arc;
+use tonic::Code;
+use tonic::service::Interceptor;
+
+const GATEWAY_KEY: &str = "gateway.s3cr3t";
+
+fn authenticator() -> Authenticator {
+    let mut store = StaticKeyStore::default();
+    store.insert("gateway".to_string(), AccessLevel::Operator, StaticKeyStore::digest(GATEWAY_KEY)).unwrap();
+    Authenticator::new(Arc::new(store), MethodPolicy::default())
+}
+
+/// Authorize a call to `method` made through `interceptor` as the runtime would
+async fn authorize(authenticator: &Authenticator, mut interceptor: RuntimeInterceptor, method: &str) -> Result<Option<String>, Code> {
+    let request = interceptor.call(tonic::Request::new(())).unwrap();
+    let headers = request.metadata().clone().into_headers();
+    authenticator
+        .authorize(method, &headers)
+        .await
+        .map(|credential| credential.map(|c| c.key_id))
+        .map_err(|status| status.code())
+}
+
+#[tokio::test]
+async fn test_configured_key_is_accepted_by_the_runtime() {
+    let authenticator = authenticator();
+    let pool = ChannelPool::connect_lazy(
+        "http://127.0.0.1:1",
+        PoolConfig {
+            api_key: Some(GATEWAY_KEY.to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    assert_eq!(authorize(&authenticator, pool.interceptor(), "/vm_service.VmService/DeployDot").await, Ok(Some("gateway".to_string())));
+    assert_eq!(
+        authorize(&authenticator, pool.interceptor(), "/database_service.DatabaseService/ApplyWriteBatch").await,
+        Ok(Some("gateway".to_string()))
+    );
+    // The gateway key is an operator key, so admin methods stay refused
+    assert_eq!(authorize(&authenticator, pool.interceptor(), "/vm_service.VmService/DeleteDot").await, Err(Code::PermissionDenied));
+}
+
+#[tokio::test]
+async fn test_calls_without_a_configured_key_are_refused() {
+    let authenticator = authenticator();
+    let pool = ChannelPool::connect_lazy("http://127.0.0.1:1", PoolConfig::default()).unwrap();
+    assert_eq!(authorize(&authenticator, pool.interceptor(), "/vm_service.VmService/DeployDot").await, Err(Code::Unauthenticated));
+
+    let wrong = RuntimeInterceptor::new(Some("gateway.guess")).unwrap();
+    assert_eq!(authorize(&authenticator, wrong, "/vm_service.VmService/DeployDot").await, Err(Code::Unauthenticated));
+
+    // Health checks stay public, so the pool's probe works before a key is configured
+    assert_eq!(authorize(&authenticator, pool.interceptor(), "/vm_service.VmService/HealthCheck").await, Ok(None));
+}
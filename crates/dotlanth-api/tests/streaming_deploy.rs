@@ -0,0 +1,93 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Memory held by the gateway while it streams a large multipart deployment
+
+use dotlanth_api::uploads::{MAX_METADATA_BYTES, MultipartUpload};
+use futures::stream;
+use http_body_util::StreamBody;
+use hyper::body::{Bytes, Frame};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts live heap bytes and their high-water mark
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            PEAK.fetch_max(allocated, Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        ALLOCATED.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const BOUNDARY: &str = "dotlanth-upload-boundary";
+const BYTECODE_BYTES: usize = 300 * 1024 * 1024;
+const FRAME_BYTES: usize = 256 * 1024;
+const CHUNK_BYTES: usize = 64 * 1024;
+
+/// Bytecode frames served from a static buffer, so only the gateway's own buffers allocate
+static FRAME: [u8; FRAME_BYTES] = [0x5a; FRAME_BYTES];
+
+/// A multipart deployment of `BYTECODE_BYTES` of bytecode, produced as it is read
+fn deployment_body() -> StreamBody<impl futures::Stream<Item = Result<Frame<Bytes>, Infallible>> + Unpin> {
+    let head = format!(
+        "--{boundary}\r\nContent-Disposition: form-data; name=\"metadata\"\r\n\r\n{{\"name\":\"big\"}}\r\n--{boundary}\r\nContent-Disposition: form-data; name=\"bytecode\"\r\nContent-Type: application/octet-stream\r\n\r\n",
+        boundary = BOUNDARY
+    );
+    let tail = format!("\r\n--{}--\r\n", BOUNDARY);
+    let bytecode = (0..BYTECODE_BYTES / FRAME_BYTES).map(|_| Bytes::from_static(&FRAME));
+
+    let frames = std::iter::once(Bytes::from(head)).chain(bytecode).chain(std::iter::once(Bytes::from(tail)));
+    StreamBody::new(stream::iter(frames.map(|frame| Ok(Frame::data(frame)))))
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_300mb_deployment_is_streamed_in_bounded_memory() {
+    let baseline = ALLOCATED.load(Ordering::SeqCst);
+    PEAK.store(baseline, Ordering::SeqCst);
+
+    let mut upload = MultipartUpload::new(deployment_body(), BOUNDARY, CHUNK_BYTES);
+    assert_eq!(upload.next_part().await.unwrap().as_deref(), Some("metadata"));
+    assert_eq!(upload.read_part(MAX_METADATA_BYTES).await.unwrap(), br#"{"name":"big"}"#);
+    assert_eq!(upload.next_part().await.unwrap().as_deref(), Some("bytecode"));
+
+    let mut received = 0;
+    while let Some(chunk) = upload.next_chunk().await.unwrap() {
+        assert_eq!(chunk.len(), CHUNK_BYTES.min(BYTECODE_BYTES - received));
+        received += chunk.len();
+    }
+    assert_eq!(received, BYTECODE_BYTES);
+    assert_eq!(upload.next_part().await.unwrap(), None);
+
+    let peak_growth = PEAK.load(Ordering::SeqCst) - baseline;
+    assert!(peak_growth < 4 * 1024 * 1024, "streaming {} bytes peaked at {} extra heap bytes", BYTECODE_BYTES, peak_growth);
+}
@@ -0,0 +1,351 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Multiplexed WebSocket subscriptions driven through a real client
+
+use async_trait::async_trait;
+use dotlanth_api::auth::{AuthService, Claims, JwtManager};
+use dotlanth_api::error::ApiResult;
+use dotlanth_api::grpc_pool::PoolConfig;
+use dotlanth_api::models::DotEvent;
+use dotlanth_api::shutdown::Shutdown;
+use dotlanth_api::vm::VmClient;
+use dotlanth_api::websocket::WebSocketManager;
+use dotlanth_api::websocket_mux::{LOG_EVENT_TYPE, MultiplexConfig, Multiplexer, UpstreamItem, UpstreamSource, UpstreamTopic};
+use futures::stream::BoxStream;
+use futures::{SinkExt, StreamExt};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper_util::rt::TokioIo;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{Mutex, broadcast};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+const JWT_SECRET: &str = "websocket-multiplexing-test";
+
+type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Upstream streams fed by the test, counting how often each topic is opened
+#[derive(Default)]
+struct FakeUpstream {
+    topics: parking_lot::Mutex<HashMap<UpstreamTopic, broadcast::Sender<UpstreamItem>>>,
+    opened: AtomicUsize,
+}
+
+impl FakeUpstream {
+    fn sender(&self, topic: UpstreamTopic) -> broadcast::Sender<UpstreamItem> {
+        self.topics.lock().entry(topic).or_insert_with(|| broadcast::channel(4096).0).clone()
+    }
+
+    fn publish(&self, topic: UpstreamTopic, item: UpstreamItem) {
+        let _ = self.sender(topic).send(item);
+    }
+}
+
+#[async_trait]
+impl UpstreamSource for FakeUpstream {
+    async fn open(&self, topic: UpstreamTopic) -> ApiResult<BoxStream<'static, ApiResult<UpstreamItem>>> {
+        let receiver = self.sender(topic).subscribe();
+        self.opened.fetch_add(1, Ordering::SeqCst);
+        Ok(futures::stream::unfold(receiver, |mut receiver| async move { receiver.recv().await.ok().map(|item| (Ok(item), receiver)) }).boxed())
+    }
+}
+
+fn dot_event(dot_id: &str, event_type: &str, data: Value) -> UpstreamItem {
+    UpstreamItem::DotEvent(DotEvent {
+        event_id: uuid::Uuid::new_v4().to_string(),
+        dot_id: dot_id.to_string(),
+        event_type: event_type.to_string(),
+        data,
+        metadata: HashMap::new(),
+    })
+}
+
+struct Gateway {
+    address: std::net::SocketAddr,
+    upstream: Arc<FakeUpstream>,
+    multiplexer: Arc<Multiplexer>,
+    shutdown: Shutdown,
+}
+
+impl Gateway {
+    async fn start(config: MultiplexConfig) -> Self {
+        let upstream = Arc::new(FakeUpstream::default());
+        let multiplexer = Arc::new(Multiplexer::new(upstream.clone(), config));
+        let shutdown = Shutdown::new();
+        let vm_client = VmClient::connect_lazy("http://127.0.0.1:1", PoolConfig::default()).unwrap();
+        let auth_service = Arc::new(Mutex::new(AuthService::new(JWT_SECRET)));
+        let manager = Arc::new(WebSocketManager::new(vm_client, auth_service, shutdown.clone()).with_multiplexer(multiplexer.clone()));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let manager = manager.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |req| {
+                        let manager = manager.clone();
+                        async move { manager.handle_websocket_upgrade(req).await }
+                    });
+                    let _ = http1::Builder::new().serve_connection(TokioIo::new(stream), service).with_upgrades().await;
+                });
+            }
+        });
+
+        Self {
+            address,
+            upstream,
+            multiplexer,
+            shutdown,
+        }
+    }
+
+    async fn connect_with(&self, permissions: &[&str]) -> Client {
+        let claims = Claims::new(
+            "dashboard".to_string(),
+            vec!["user".to_string()],
+            permissions.iter().map(|p| p.to_string()).collect(),
+            chrono::Duration::hours(1),
+        );
+        let token = JwtManager::new(JWT_SECRET).create_token(&claims).unwrap();
+
+        let mut request = format!("ws://{}/api/v1/ws", self.address).into_client_request().unwrap();
+        request.headers_mut().insert("Authorization", format!("Bearer {}", token).parse().unwrap());
+        let (client, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+        client
+    }
+
+    async fn connect(&self) -> Client {
+        self.connect_with(&["execute:dots"]).await
+    }
+
+    /// Wait until the number of open upstream streams reaches `expected`
+    async fn wait_for_upstreams(&self, expected: usize) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while self.multiplexer.active_upstreams() != expected {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("expected {} upstream streams, found {}", expected, self.multiplexer.active_upstreams()));
+    }
+
+    /// Wait until the fake upstream has been opened `expected` times, so published items reach it
+    async fn wait_for_opened(&self, expected: usize) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while self.upstream.opened.load(Ordering::SeqCst) < expected {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("upstream was opened {} times, expected {}", self.upstream.opened.load(Ordering::SeqCst), expected));
+    }
+}
+
+async fn send(client: &mut Client, frame: Value) {
+    client.send(Message::Text(frame.to_string())).await.unwrap();
+}
+
+/// Next multiplexing frame, skipping anything else the server sends
+async fn next_frame(client: &mut Client) -> Value {
+    loop {
+        let message = tokio::time::timeout(Duration::from_secs(5), client.next())
+            .await
+            .expect("timed out waiting for a frame")
+            .expect("connection closed")
+            .unwrap();
+        if let Message::Text(text) = message {
+            let frame: Value = serde_json::from_str(&text).unwrap();
+            if frame.get("op").is_some() {
+                return frame;
+            }
+        }
+    }
+}
+
+async fn subscribe(client: &mut Client, channel: &str, topic: Value) {
+    let mut frame = json!({"op": "subscribe", "channel": channel});
+    frame.as_object_mut().unwrap().extend(topic.as_object().unwrap().clone());
+    send(client, frame).await;
+    let reply = next_frame(client).await;
+    assert_eq!(reply["op"], "subscribed", "unexpected reply {}", reply);
+    assert_eq!(reply["channel"], channel);
+}
+
+#[tokio::test]
+async fn channels_share_one_upstream_per_topic() {
+    let gateway = Gateway::start(MultiplexConfig::default()).await;
+    let mut first = gateway.connect().await;
+    let mut second = gateway.connect().await;
+
+    subscribe(&mut first, "a", json!({"topic": "dot_events", "dot_id": "dot-a"})).await;
+    subscribe(&mut first, "b", json!({"topic": "dot_events", "dot_id": "dot-b"})).await;
+    subscribe(&mut first, "logs", json!({"topic": "logs"})).await;
+    subscribe(&mut second, "everything", json!({"topic": "dot_events"})).await;
+    gateway.wait_for_opened(2).await;
+
+    gateway.upstream.publish(UpstreamTopic::DotEvents, dot_event("dot-b", "executed", json!({"n": 1})));
+    gateway.upstream.publish(UpstreamTopic::DotEvents, dot_event("dot-a", "executed", json!({"n": 2})));
+    gateway.upstream.publish(UpstreamTopic::Logs, dot_event("dot-a", LOG_EVENT_TYPE, json!({"line": "hello"})));
+
+    let mut received = HashMap::new();
+    for _ in 0..3 {
+        let frame = next_frame(&mut first).await;
+        assert_eq!(frame["op"], "message");
+        assert_eq!(frame["seq"], 1);
+        received.insert(frame["channel"].as_str().unwrap().to_string(), frame["data"].clone());
+    }
+    assert_eq!(received["a"]["dot_id"], "dot-a");
+    assert_eq!(received["b"]["dot_id"], "dot-b");
+    assert_eq!(received["logs"]["data"]["line"], "hello");
+
+    let first_seen = next_frame(&mut second).await;
+    let second_seen = next_frame(&mut second).await;
+    assert_eq!((first_seen["channel"].as_str(), first_seen["seq"].as_u64()), (Some("everything"), Some(1)));
+    assert_eq!((second_seen["data"]["dot_id"].as_str(), second_seen["seq"].as_u64()), (Some("dot-a"), Some(2)));
+
+    // Four channels on two connections, one upstream call per topic
+    assert_eq!(gateway.upstream.opened.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn subscription_limits_and_errors() {
+    let gateway = Gateway::start(MultiplexConfig {
+        max_channels_per_connection: 2,
+        ..MultiplexConfig::default()
+    })
+    .await;
+    let mut client = gateway.connect().await;
+
+    subscribe(&mut client, "one", json!({"topic": "dot_events"})).await;
+    send(&mut client, json!({"op": "subscribe", "channel": "one", "topic": "logs"})).await;
+    let reply = next_frame(&mut client).await;
+    assert_eq!((reply["op"].as_str(), reply["code"].as_str()), (Some("error"), Some("duplicate_channel")));
+
+    subscribe(&mut client, "two", json!({"topic": "vm_metrics"})).await;
+    send(&mut client, json!({"op": "subscribe", "channel": "three", "topic": "logs"})).await;
+    let reply = next_frame(&mut client).await;
+    assert_eq!((reply["channel"].as_str(), reply["code"].as_str()), (Some("three"), Some("subscription_limit")));
+
+    send(&mut client, json!({"op": "unsubscribe", "channel": "missing"})).await;
+    assert_eq!(next_frame(&mut client).await["code"], "unknown_channel");
+
+    send(&mut client, json!({"op": "subscribe", "channel": "bad", "topic": "weather"})).await;
+    let reply = next_frame(&mut client).await;
+    assert_eq!(reply["code"], "invalid_frame");
+    assert!(reply.get("channel").is_none());
+
+    // Closing a channel frees its slot
+    send(&mut client, json!({"op": "unsubscribe", "channel": "one"})).await;
+    let reply = next_frame(&mut client).await;
+    assert_eq!((reply["op"].as_str(), reply["reason"].as_str()), (Some("unsubscribed"), Some("client")));
+    subscribe(&mut client, "three", json!({"topic": "logs"})).await;
+
+    let mut read_only = gateway.connect_with(&["read:documents"]).await;
+    send(&mut read_only, json!({"op": "subscribe", "channel": "one", "topic": "dot_events"})).await;
+    assert_eq!(next_frame(&mut read_only).await["code"], "forbidden");
+}
+
+#[tokio::test]
+async fn slow_subscriber_is_evicted() {
+    let gateway = Gateway::start(MultiplexConfig {
+        channel_buffer: 8,
+        ..MultiplexConfig::default()
+    })
+    .await;
+    let mut slow = gateway.connect().await;
+    let mut fast = gateway.connect().await;
+    subscribe(&mut slow, "flood", json!({"topic": "dot_events"})).await;
+    subscribe(&mut fast, "flood", json!({"topic": "dot_events", "event_types": ["rare"]})).await;
+    gateway.wait_for_opened(1).await;
+
+    // The slow client stops reading; large messages fill the socket buffers and then its channel buffer
+    let payload = "x".repeat(64 * 1024);
+    for _ in 0..400 {
+        gateway.upstream.publish(UpstreamTopic::DotEvents, dot_event("dot-a", "bulk", json!({ "payload": payload })));
+    }
+    gateway.upstream.publish(UpstreamTopic::DotEvents, dot_event("dot-a", "rare", json!({})));
+
+    // The other subscriber of the same upstream is unaffected
+    let frame = next_frame(&mut fast).await;
+    assert_eq!((frame["op"].as_str(), frame["data"]["type"].as_str()), (Some("message"), Some("rare")));
+
+    let mut delivered = 0;
+    let evicted = loop {
+        let frame = next_frame(&mut slow).await;
+        match frame["op"].as_str() {
+            Some("message") => {
+                delivered += 1;
+                assert_eq!(frame["seq"], delivered);
+            }
+            Some("unsubscribed") => break frame,
+            _ => panic!("unexpected frame {}", frame),
+        }
+    };
+    assert_eq!(evicted["channel"], "flood");
+    assert_eq!(evicted["reason"], "slow_consumer");
+    assert!(delivered < 400, "channel was never evicted");
+
+    // The connection stays usable after the eviction
+    subscribe(&mut slow, "flood", json!({"topic": "logs"})).await;
+}
+
+#[tokio::test]
+async fn closing_the_socket_releases_upstreams() {
+    let gateway = Gateway::start(MultiplexConfig::default()).await;
+    let mut first = gateway.connect().await;
+    let mut second = gateway.connect().await;
+
+    subscribe(&mut first, "events", json!({"topic": "dot_events"})).await;
+    subscribe(&mut first, "metrics", json!({"topic": "vm_metrics"})).await;
+    subscribe(&mut second, "events", json!({"topic": "dot_events"})).await;
+    gateway.wait_for_upstreams(2).await;
+
+    // Metrics had only the first connection's channel; events are still watched by the second
+    first.close(None).await.unwrap();
+    gateway.wait_for_upstreams(1).await;
+
+    send(&mut second, json!({"op": "unsubscribe", "channel": "events"})).await;
+    assert_eq!(next_frame(&mut second).await["reason"], "client");
+    gateway.wait_for_upstreams(0).await;
+
+    // A new subscriber reopens the upstream
+    subscribe(&mut second, "events", json!({"topic": "dot_events"})).await;
+    gateway.wait_for_upstreams(1).await;
+    gateway.wait_for_opened(3).await;
+}
+
+#[tokio::test]
+async fn shutdown_unsubscribes_open_channels() {
+    let gateway = Gateway::start(MultiplexConfig::default()).await;
+    let mut client = gateway.connect().await;
+    subscribe(&mut client, "events", json!({"topic": "dot_events"})).await;
+
+    gateway.shutdown.trigger();
+
+    let frame = next_frame(&mut client).await;
+    assert_eq!((frame["op"].as_str(), frame["reason"].as_str()), (Some("unsubscribed"), Some("shutdown")));
+    let close = tokio::time::timeout(Duration::from_secs(5), client.next()).await.unwrap().unwrap().unwrap();
+    assert!(close.is_close());
+    gateway.wait_for_upstreams(0).await;
+}
@@ -0,0 +1,422 @@
+//! Block-level full and incremental backups of the data directory
+//!
+//! A backup's manifest lists every file of the data directory as fixed-size blocks
+//! with their CRC32 checksums. A full backup stores every block itself; an
+//! incremental one stores only the blocks whose checksum differs from its base and
+//! points at the backup already holding each unchanged block. Every manifest
+//! therefore describes the complete state, and restoring reads blocks straight from
+//! the backups of the chain that hold them.
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use dotdb_core::storage_engine::calculate_checksum;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// Directory of the data directory holding backups; it is never backed up itself
+pub const BACKUPS_DIR: &str = "backups";
+const MANIFEST_FILE: &str = "backup.json";
+const BLOCKS_DIR: &str = "blocks";
+
+/// Unit changes are tracked and copied in
+pub const BLOCK_SIZE: u64 = 4 * 1024 * 1024;
+
+/// One block of a backed up file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockRef {
+    pub checksum: u32,
+    /// Backup whose block store holds the bytes
+    pub backup: String,
+    /// Position in that backup's block store
+    pub id: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileEntry {
+    pub size: u64,
+    pub blocks: Vec<BlockRef>,
+}
+
+/// Contents of a backup's `backup.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    /// Backup this one was taken against; `None` for a full backup
+    pub base: Option<String>,
+    pub block_size: u64,
+    /// Files by `/`-separated path relative to the data directory
+    pub files: BTreeMap<String, FileEntry>,
+}
+
+impl BackupManifest {
+    pub fn total_size(&self) -> u64 {
+        self.files.values().map(|file| file.size).sum()
+    }
+
+    /// Backups whose block stores a restore reads from, including this one
+    pub fn dependencies(&self) -> BTreeSet<&str> {
+        self.files.values().flat_map(|file| &file.blocks).map(|block| block.backup.as_str()).collect()
+    }
+
+    fn block_len(&self, file: &FileEntry, index: usize) -> u64 {
+        (file.size - index as u64 * self.block_size).min(self.block_size)
+    }
+}
+
+/// A block that cannot be restored as recorded
+#[derive(Debug, Clone)]
+pub struct BlockMismatch {
+    pub file: String,
+    pub index: usize,
+    pub offset: u64,
+    /// Backup expected to hold the block
+    pub backup: String,
+    pub problem: String,
+}
+
+impl fmt::Display for BlockMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} block {} (offset {}) in backup '{}': {}", self.file, self.index, self.offset, self.backup, self.problem)
+    }
+}
+
+/// What a backup copied
+#[derive(Debug, Clone)]
+pub struct CreateSummary {
+    pub manifest: BackupManifest,
+    pub blocks_copied: usize,
+    pub blocks_reused: usize,
+    pub bytes_copied: u64,
+}
+
+#[derive(Debug, Clone)]
+pub enum RestoreOutcome {
+    Restored {
+        files: usize,
+        bytes: u64,
+    },
+    /// Nothing was written to the data directory
+    Refused(Vec<BlockMismatch>),
+}
+
+/// Backups removed by a prune
+#[derive(Debug, Clone, Default)]
+pub struct PruneSummary {
+    pub deleted: Vec<String>,
+    /// Older backups kept because a retained backup depends on them
+    pub kept_as_base: Vec<String>,
+}
+
+/// Backups kept below `<data_dir>/backups`
+pub struct BackupStore {
+    data_dir: PathBuf,
+    root: PathBuf,
+}
+
+impl BackupStore {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            data_dir: data_dir.to_path_buf(),
+            root: data_dir.join(BACKUPS_DIR),
+        }
+    }
+
+    pub fn backup_dir(&self, name: &str) -> PathBuf {
+        self.root.join(name)
+    }
+
+    fn block_path(backup_dir: &Path, id: u64) -> PathBuf {
+        backup_dir.join(BLOCKS_DIR).join(format!("{:08}.blk", id))
+    }
+
+    pub fn load(&self, name: &str) -> Result<BackupManifest> {
+        let path = self.backup_dir(name).join(MANIFEST_FILE);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => bail!("Backup '{}' not found", name),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        serde_json::from_str(&content).with_context(|| format!("Backup '{}' has no valid block manifest at {}", name, path.display()))
+    }
+
+    /// Every readable backup, oldest first. Directories without a valid manifest are
+    /// skipped, so they are never pruned.
+    pub fn list(&self) -> Result<Vec<BackupManifest>> {
+        let entries = match std::fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", self.root.display())),
+        };
+
+        let mut backups = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') || !entry.file_type()?.is_dir() {
+                continue;
+            }
+            match self.load(&name) {
+                Ok(manifest) => backups.push(manifest),
+                Err(e) => tracing::warn!("Skipping backup directory {}: {:#}", name, e),
+            }
+        }
+        backups.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.name.cmp(&b.name)));
+        Ok(backups)
+    }
+
+    /// Back up the data directory as `name`, storing only blocks that differ from
+    /// `base` when one is given
+    pub fn create(&self, name: &str, base: Option<&str>) -> Result<CreateSummary> {
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            bail!("Invalid backup name '{}'", name);
+        }
+        let backup_dir = self.backup_dir(name);
+        if backup_dir.exists() {
+            bail!("Backup '{}' already exists", name);
+        }
+        let base = base.map(|base| self.load(base)).transpose()?;
+        if let Some(base) = &base
+            && base.block_size != BLOCK_SIZE
+        {
+            bail!("Base backup '{}' uses {}-byte blocks, expected {}", base.name, base.block_size, BLOCK_SIZE);
+        }
+
+        // Build the backup under a hidden name so an interrupted run leaves no usable-looking backup
+        let partial_dir = self.root.join(format!(".{}.partial", name));
+        if partial_dir.exists() {
+            std::fs::remove_dir_all(&partial_dir)?;
+        }
+        std::fs::create_dir_all(partial_dir.join(BLOCKS_DIR))?;
+
+        let mut manifest = BackupManifest {
+            name: name.to_string(),
+            created_at: Utc::now(),
+            base: base.as_ref().map(|base| base.name.clone()),
+            block_size: BLOCK_SIZE,
+            files: BTreeMap::new(),
+        };
+        let (mut blocks_copied, mut blocks_reused, mut bytes_copied) = (0, 0, 0);
+        let mut buf = Vec::with_capacity(BLOCK_SIZE as usize);
+
+        for relative in self.data_files()? {
+            let path = self.data_dir.join(&relative);
+            let mut file = File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?;
+            let base_file = base.as_ref().and_then(|base| base.files.get(&relative).map(|file| (base, file)));
+
+            let mut entry = FileEntry { size: 0, blocks: Vec::new() };
+            loop {
+                buf.clear();
+                let len = (&mut file).take(BLOCK_SIZE).read_to_end(&mut buf)?;
+                if len == 0 {
+                    break;
+                }
+                let index = entry.blocks.len();
+                let checksum = calculate_checksum(&buf);
+
+                let unchanged = base_file.and_then(|(base, base_file)| base_file.blocks.get(index).filter(|block| block.checksum == checksum && base.block_len(base_file, index) == len as u64));
+                match unchanged {
+                    Some(block) => {
+                        entry.blocks.push(block.clone());
+                        blocks_reused += 1;
+                    }
+                    None => {
+                        let id = blocks_copied as u64;
+                        std::fs::write(Self::block_path(&partial_dir, id), &buf)?;
+                        entry.blocks.push(BlockRef {
+                            checksum,
+                            backup: name.to_string(),
+                            id,
+                        });
+                        blocks_copied += 1;
+                        bytes_copied += len as u64;
+                    }
+                }
+                entry.size += len as u64;
+            }
+            manifest.files.insert(relative, entry);
+        }
+
+        let mut manifest_file = File::create(partial_dir.join(MANIFEST_FILE))?;
+        manifest_file.write_all(serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+        manifest_file.sync_all()?;
+        std::fs::rename(&partial_dir, &backup_dir)?;
+
+        Ok(CreateSummary {
+            manifest,
+            blocks_copied,
+            blocks_reused,
+            bytes_copied,
+        })
+    }
+
+    /// Check every block a backup's restore would read; an empty list means it is intact
+    pub fn verify(&self, name: &str) -> Result<Vec<BlockMismatch>> {
+        let manifest = self.load(name)?;
+        let mut mismatches = Vec::new();
+        for (path, file) in &manifest.files {
+            for index in 0..file.blocks.len() {
+                if let Err(mismatch) = self.read_block(&manifest, path, file, index) {
+                    mismatches.push(mismatch);
+                }
+            }
+        }
+        Ok(mismatches)
+    }
+
+    /// Rebuild the data directory from a backup. Files are assembled and verified in
+    /// a staging directory first, and only moved into place if every block matches
+    /// its checksum. Files created after the backup are left as they are.
+    pub fn restore(&self, name: &str) -> Result<RestoreOutcome> {
+        let manifest = self.load(name)?;
+        for path in manifest.files.keys() {
+            if !Path::new(path).components().all(|component| matches!(component, Component::Normal(_))) || path.starts_with(&format!("{}/", BACKUPS_DIR)) {
+                bail!("Backup '{}' lists an unsafe path: {}", name, path);
+            }
+        }
+
+        let staging_dir = self.root.join(format!(".restore-{}", name));
+        if staging_dir.exists() {
+            std::fs::remove_dir_all(&staging_dir)?;
+        }
+
+        // Keep checking after the first mismatch so the report is complete, but stop writing
+        let mut mismatches = Vec::new();
+        for (path, file) in &manifest.files {
+            let staged = staging_dir.join(path);
+            let mut output = None;
+            if mismatches.is_empty() {
+                std::fs::create_dir_all(staged.parent().unwrap_or(&staging_dir))?;
+                output = Some(File::create(&staged).with_context(|| format!("Failed to create {}", staged.display()))?);
+            }
+            for index in 0..file.blocks.len() {
+                match self.read_block(&manifest, path, file, index) {
+                    Ok(data) => {
+                        if let Some(output) = output.as_mut().filter(|_| mismatches.is_empty()) {
+                            output.write_all(&data)?;
+                        }
+                    }
+                    Err(mismatch) => mismatches.push(mismatch),
+                }
+            }
+            if let Some(output) = output {
+                output.sync_all()?;
+            }
+        }
+
+        if !mismatches.is_empty() {
+            let _ = std::fs::remove_dir_all(&staging_dir);
+            return Ok(RestoreOutcome::Refused(mismatches));
+        }
+
+        for path in manifest.files.keys() {
+            let target = self.data_dir.join(path);
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::rename(staging_dir.join(path), &target).with_context(|| format!("Failed to move restored {} into place", path))?;
+        }
+        if staging_dir.exists() {
+            std::fs::remove_dir_all(&staging_dir)?;
+        }
+
+        Ok(RestoreOutcome::Restored {
+            files: manifest.files.len(),
+            bytes: manifest.total_size(),
+        })
+    }
+
+    /// Delete all but the `keep_last` most recent backups, keeping every backup a
+    /// retained one is based on, directly or further down its chain
+    pub fn prune(&self, keep_last: usize) -> Result<PruneSummary> {
+        if keep_last == 0 {
+            bail!("--keep-last must be at least 1");
+        }
+        let backups = self.list()?;
+        let by_name: HashMap<&str, &BackupManifest> = backups.iter().map(|backup| (backup.name.as_str(), backup)).collect();
+        let retained: Vec<&BackupManifest> = backups.iter().rev().take(keep_last).collect();
+
+        let mut keep: BTreeSet<&str> = BTreeSet::new();
+        let mut pending: Vec<&str> = retained.iter().map(|backup| backup.name.as_str()).collect();
+        while let Some(name) = pending.pop() {
+            if !keep.insert(name) {
+                continue;
+            }
+            if let Some(backup) = by_name.get(name) {
+                pending.extend(backup.base.as_deref());
+                pending.extend(backup.dependencies());
+            }
+        }
+
+        let mut summary = PruneSummary::default();
+        for backup in &backups {
+            if !keep.contains(backup.name.as_str()) {
+                std::fs::remove_dir_all(self.backup_dir(&backup.name)).with_context(|| format!("Failed to delete backup '{}'", backup.name))?;
+                summary.deleted.push(backup.name.clone());
+            } else if !retained.iter().any(|retained| retained.name == backup.name) {
+                summary.kept_as_base.push(backup.name.clone());
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Read block `index` of `file`, checking its length and checksum
+    fn read_block(&self, manifest: &BackupManifest, path: &str, file: &FileEntry, index: usize) -> Result<Vec<u8>, BlockMismatch> {
+        let block = &file.blocks[index];
+        let mismatch = |problem: String| BlockMismatch {
+            file: path.to_string(),
+            index,
+            offset: index as u64 * manifest.block_size,
+            backup: block.backup.clone(),
+            problem,
+        };
+
+        let block_path = Self::block_path(&self.backup_dir(&block.backup), block.id);
+        let data = std::fs::read(&block_path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => mismatch(format!("block file {} is missing", block_path.display())),
+            _ => mismatch(format!("failed to read {}: {}", block_path.display(), e)),
+        })?;
+
+        let expected_len = manifest.block_len(file, index);
+        if data.len() as u64 != expected_len {
+            return Err(mismatch(format!("expected {} bytes, found {}", expected_len, data.len())));
+        }
+        let checksum = calculate_checksum(&data);
+        if checksum != block.checksum {
+            return Err(mismatch(format!("checksum mismatch: expected {:08x}, found {:08x}", block.checksum, checksum)));
+        }
+        Ok(data)
+    }
+
+    /// Regular files of the data directory outside the backups directory, as manifest paths
+    fn data_files(&self) -> Result<Vec<String>> {
+        let mut files = Vec::new();
+        let mut pending = vec![(self.data_dir.clone(), String::new())];
+        while let Some((dir, prefix)) = pending.pop() {
+            let entries = match std::fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound && prefix.is_empty() => break,
+                Err(e) => return Err(e).with_context(|| format!("Failed to read {}", dir.display())),
+            };
+            for entry in entries {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let relative = if prefix.is_empty() { name.clone() } else { format!("{}/{}", prefix, name) };
+                let file_type = entry.file_type()?;
+                if file_type.is_dir() {
+                    if relative != BACKUPS_DIR {
+                        pending.push((entry.path(), relative));
+                    }
+                } else if file_type.is_file() {
+                    files.push(relative);
+                }
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+}
@@ -0,0 +1,110 @@
+use super::{CommandContext, call_gateway, field};
+use crate::{ApiKeyCommands, ApiKeyScope};
+use anyhow::{Result, anyhow};
+use serde_json::{Value, json};
+
+const API_KEYS_PATH: &str = "/api/v1/admin/api-keys";
+
+pub fn handle_apikey_command(ctx: &CommandContext, command: ApiKeyCommands) -> Result<()> {
+    match command {
+        ApiKeyCommands::Create { name, scopes, dots } => create_key(ctx, &name, &scopes, dots),
+        ApiKeyCommands::List => list_keys(ctx),
+        ApiKeyCommands::Rotate { id } => {
+            let response = call_gateway(ctx, "POST", &format!("{}/{}/rotate", API_KEYS_PATH, id), None)?;
+            println!("Rotated API key {}; the old secret keeps working until the gateway's grace period ends", id);
+            print_secret(&response);
+            Ok(())
+        }
+        ApiKeyCommands::Revoke { id } => {
+            let response = call_gateway(ctx, "DELETE", &format!("{}/{}", API_KEYS_PATH, id), None)?;
+            println!("Revoked API key {} ({}) at {}", id, field(&response, "name"), field(&response, "revoked_at"));
+            Ok(())
+        }
+    }
+}
+
+fn scope_name(scope: ApiKeyScope) -> &'static str {
+    match scope {
+        ApiKeyScope::Read => "read",
+        ApiKeyScope::Execute => "execute",
+        ApiKeyScope::Deploy => "deploy",
+        ApiKeyScope::Admin => "admin",
+    }
+}
+
+fn create_key(ctx: &CommandContext, name: &str, scopes: &[ApiKeyScope], dots: Vec<String>) -> Result<()> {
+    let request = json!({
+        "name": name,
+        "scopes": scopes.iter().map(|&scope| scope_name(scope)).collect::<Vec<_>>(),
+        "dots": (!dots.is_empty()).then_some(dots),
+    });
+    let response = call_gateway(ctx, "POST", API_KEYS_PATH, Some(&request))?;
+    println!("Created API key {} ({})", field(&response["key"], "id"), name);
+    print_secret(&response);
+    Ok(())
+}
+
+/// Print the secret of a created or rotated key, which the gateway only returns once
+fn print_secret(response: &Value) {
+    let key = &response["key"];
+    println!("  ID:     {}", field(key, "id"));
+    println!("  Scopes: {}", join(&key["scopes"]));
+    println!("  Dots:   {}", if key["dots"].is_null() { "any".to_string() } else { join(&key["dots"]) });
+    println!();
+    println!("  Secret: {}", field(response, "secret"));
+    println!();
+    println!("Store the secret now; it cannot be shown again. Send it in the X-API-Key header.");
+}
+
+fn list_keys(ctx: &CommandContext) -> Result<()> {
+    let response = call_gateway(ctx, "GET", API_KEYS_PATH, None)?;
+    let keys = response.as_array().ok_or_else(|| anyhow!("Unexpected response from {}", API_KEYS_PATH))?;
+    if keys.is_empty() {
+        println!("No API keys");
+        return Ok(());
+    }
+
+    println!("{:<36} {:<20} {:<14} {:<24} {:<20} {:<20} {:<8}", "ID", "Name", "Prefix", "Scopes", "Created", "Last used", "Status");
+    println!("{}", "-".repeat(148));
+    for key in keys {
+        println!(
+            "{:<36} {:<20} {:<14} {:<24} {:<20} {:<20} {:<8}",
+            field(key, "id"),
+            field(key, "name"),
+            field(key, "prefix"),
+            join(&key["scopes"]),
+            timestamp(&key["created_at"]),
+            timestamp(&key["last_used_at"]),
+            status(key)
+        );
+    }
+    Ok(())
+}
+
+fn join(values: &Value) -> String {
+    values
+        .as_array()
+        .map(|values| values.iter().filter_map(Value::as_str).collect::<Vec<_>>().join(","))
+        .unwrap_or_default()
+}
+
+/// An RFC 3339 timestamp shortened to the minute, or `-` when unset
+fn timestamp(value: &Value) -> String {
+    match value.as_str().and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok()) {
+        Some(time) => time.format("%Y-%m-%d %H:%M").to_string(),
+        None => "-".to_string(),
+    }
+}
+
+fn status(key: &Value) -> &'static str {
+    let expires_at = key["expires_at"].as_str().and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok());
+    if !key["revoked_at"].is_null() {
+        "revoked"
+    } else if expires_at.is_some_and(|expires_at| expires_at <= chrono::Utc::now()) {
+        "expired"
+    } else if !key["rotated_to"].is_null() {
+        "rotated"
+    } else {
+        "active"
+    }
+}
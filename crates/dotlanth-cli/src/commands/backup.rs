@@ -1,79 +1,128 @@
 use super::CommandContext;
 use crate::BackupCommands;
-use anyhow::Result;
+use crate::backup::{BackupStore, BlockMismatch, RestoreOutcome};
+use anyhow::{Result, anyhow, bail};
 
 pub fn handle_backup_command(ctx: &CommandContext, command: BackupCommands) -> Result<()> {
+    let store = BackupStore::new(&ctx.config.data_dir);
     match command {
-        BackupCommands::Create { name } => create_backup(ctx, &name),
-        BackupCommands::Restore { name } => restore_backup(ctx, &name),
+        BackupCommands::Create { name, incremental, base } => create_backup(&store, &name, incremental, base),
+        BackupCommands::Restore { name } => restore_backup(&store, &name),
+        BackupCommands::Verify { name } => verify_backups(&store, name),
+        BackupCommands::Prune { keep_last } => prune_backups(&store, keep_last),
     }
 }
 
-fn create_backup(ctx: &CommandContext, name: &str) -> Result<()> {
-    println!("Creating backup: {}", name);
-
-    let backup_dir = ctx.config.data_dir.join("backups").join(name);
-    std::fs::create_dir_all(&backup_dir)?;
-
-    // Simulate backup process
-    println!("Backing up node configurations...");
-    std::thread::sleep(std::time::Duration::from_millis(300));
-
-    println!("Backing up deployment data...");
-    std::thread::sleep(std::time::Duration::from_millis(400));
-
-    println!("Backing up metrics and logs...");
-    std::thread::sleep(std::time::Duration::from_millis(200));
-
-    // Create backup metadata
-    let backup_info = serde_json::json!({
-        "name": name,
-        "created_at": chrono::Utc::now(),
-        "nodes_count": ctx.database.list_nodes()?.len(),
-        "deployments_count": ctx.database.list_deployments()?.len(),
-        "version": "1.0.0"
-    });
-
-    let metadata_file = backup_dir.join("backup.json");
-    std::fs::write(metadata_file, serde_json::to_string_pretty(&backup_info)?)?;
+fn create_backup(store: &BackupStore, name: &str, incremental: bool, base: Option<String>) -> Result<()> {
+    let base = match (incremental, base) {
+        (false, _) => None,
+        (true, Some(base)) => Some(base),
+        (true, None) => Some(
+            store
+                .list()?
+                .pop()
+                .map(|latest| latest.name)
+                .ok_or_else(|| anyhow!("No backup to base an incremental backup on; create a full backup first"))?,
+        ),
+    };
+
+    match &base {
+        Some(base) => println!("Creating incremental backup '{}' based on '{}'", name, base),
+        None => println!("Creating full backup '{}'", name),
+    }
 
-    println!("Backup '{}' created successfully", name);
-    println!("Location: {}", backup_dir.display());
+    let summary = store.create(name, base.as_deref())?;
+    println!(
+        "Backup '{}' created successfully: {} files, {} total",
+        name,
+        summary.manifest.files.len(),
+        format_bytes(summary.manifest.total_size())
+    );
+    println!(
+        "Copied {} blocks ({}), reused {} unchanged blocks",
+        summary.blocks_copied,
+        format_bytes(summary.bytes_copied),
+        summary.blocks_reused
+    );
+    println!("Location: {}", store.backup_dir(name).display());
 
     Ok(())
 }
 
-fn restore_backup(ctx: &CommandContext, name: &str) -> Result<()> {
+fn restore_backup(store: &BackupStore, name: &str) -> Result<()> {
+    let manifest = store.load(name)?;
     println!("Restoring backup: {}", name);
-
-    let backup_dir = ctx.config.data_dir.join("backups").join(name);
-    let metadata_file = backup_dir.join("backup.json");
-
-    if !metadata_file.exists() {
-        return Err(anyhow::anyhow!("Backup '{}' not found", name));
+    println!("Backup Information:");
+    println!("  Created: {}", manifest.created_at.to_rfc3339());
+    println!("  Base: {}", manifest.base.as_deref().unwrap_or("none (full backup)"));
+    println!("  Files: {} ({})", manifest.files.len(), format_bytes(manifest.total_size()));
+    println!("  Depends on: {}", manifest.dependencies().into_iter().collect::<Vec<_>>().join(", "));
+
+    match store.restore(name)? {
+        RestoreOutcome::Restored { files, bytes } => {
+            println!("Backup '{}' restored successfully: {} files, {}", name, files, format_bytes(bytes));
+            Ok(())
+        }
+        RestoreOutcome::Refused(mismatches) => {
+            print_mismatches(&mismatches);
+            bail!("Refusing to restore '{}': {} blocks failed verification; the data directory was not modified", name, mismatches.len())
+        }
     }
+}
 
-    // Read backup metadata
-    let metadata_content = std::fs::read_to_string(metadata_file)?;
-    let backup_info: serde_json::Value = serde_json::from_str(&metadata_content)?;
-
-    println!("Backup Information:");
-    println!("  Created: {}", backup_info["created_at"].as_str().unwrap_or("unknown"));
-    println!("  Nodes: {}", backup_info["nodes_count"].as_u64().unwrap_or(0));
-    println!("  Deployments: {}", backup_info["deployments_count"].as_u64().unwrap_or(0));
+fn verify_backups(store: &BackupStore, name: Option<String>) -> Result<()> {
+    let names = match name {
+        Some(name) => vec![name],
+        None => store.list()?.into_iter().map(|backup| backup.name).collect(),
+    };
+    if names.is_empty() {
+        println!("No backups to verify");
+        return Ok(());
+    }
 
-    // Simulate restore process
-    println!("Restoring node configurations...");
-    std::thread::sleep(std::time::Duration::from_millis(400));
+    let mut failed = 0;
+    for name in &names {
+        let mismatches = store.verify(name)?;
+        if mismatches.is_empty() {
+            println!("{:<30} OK", name);
+        } else {
+            failed += 1;
+            println!("{:<30} FAILED ({} blocks)", name, mismatches.len());
+            print_mismatches(&mismatches);
+        }
+    }
 
-    println!("Restoring deployment data...");
-    std::thread::sleep(std::time::Duration::from_millis(500));
+    if failed > 0 {
+        bail!("{} of {} backups failed verification", failed, names.len());
+    }
+    Ok(())
+}
 
-    println!("Restoring metrics and logs...");
-    std::thread::sleep(std::time::Duration::from_millis(300));
+fn prune_backups(store: &BackupStore, keep_last: usize) -> Result<()> {
+    let summary = store.prune(keep_last)?;
+    for name in &summary.deleted {
+        println!("Deleted backup '{}'", name);
+    }
+    for name in &summary.kept_as_base {
+        println!("Kept backup '{}': a retained backup depends on it", name);
+    }
+    println!("Pruned {} backups", summary.deleted.len());
+    Ok(())
+}
 
-    println!("Backup '{}' restored successfully", name);
-    println!("Note: This is a placeholder implementation");
+fn print_mismatches(mismatches: &[BlockMismatch]) {
+    for mismatch in mismatches {
+        println!("  {}", mismatch);
+    }
+}
 
-    Ok(())
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 { format!("{} B", bytes) } else { format!("{:.1} {}", value, UNITS[unit]) }
 }
@@ -0,0 +1,72 @@
+use super::{CommandContext, call_vm_service, field};
+use crate::CacheCommands;
+use anyhow::Result;
+use serde_json::{Value, json};
+
+const SET_CACHING_METHOD: &str = "vm_service.VmService/SetDotCaching";
+const CACHE_STATS_METHOD: &str = "vm_service.VmService/GetDotCacheStats";
+
+pub fn handle_cache_command(ctx: &CommandContext, command: CacheCommands) -> Result<()> {
+    match command {
+        CacheCommands::Stats { dot_id } => show_stats(ctx, dot_id.as_deref()),
+        CacheCommands::Enable { dot_id } => set_caching(ctx, &dot_id, true),
+        CacheCommands::Disable { dot_id } => set_caching(ctx, &dot_id, false),
+    }
+}
+
+fn show_stats(ctx: &CommandContext, dot_id: Option<&str>) -> Result<()> {
+    let response = call_vm_service(ctx, CACHE_STATS_METHOD, &json!({ "dot_id": dot_id.unwrap_or_default() }))?;
+
+    println!(
+        "Result cache: {} entries, {} of {} bytes, {} evictions",
+        field(&response, "entries"),
+        field(&response, "bytes"),
+        field(&response, "capacityBytes"),
+        field(&response, "evictions")
+    );
+    println!("Hit rate: {} ({} hits, {} misses)", percent(&response["hitRate"]), field(&response, "hits"), field(&response, "misses"));
+    println!();
+
+    let dots = response["dots"].as_array().cloned().unwrap_or_default();
+    if dots.is_empty() {
+        println!("No deterministic dots have executed yet");
+        return Ok(());
+    }
+
+    println!(
+        "{:<36} {:<8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>12}",
+        "Dot", "Caching", "Hit rate", "Hits", "Misses", "Bypass", "Entries", "Bytes"
+    );
+    println!("{}", "-".repeat(104));
+    for dot in &dots {
+        println!(
+            "{:<36} {:<8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>12}",
+            field(dot, "dotId"),
+            if dot["enabled"].as_bool().unwrap_or(false) { "on" } else { "off" },
+            percent(&dot["hitRate"]),
+            field(dot, "hits"),
+            field(dot, "misses"),
+            field(dot, "bypasses"),
+            field(dot, "entries"),
+            field(dot, "bytes")
+        );
+    }
+
+    Ok(())
+}
+
+fn set_caching(ctx: &CommandContext, dot_id: &str, enabled: bool) -> Result<()> {
+    let response = call_vm_service(ctx, SET_CACHING_METHOD, &json!({ "dot_id": dot_id, "enabled": enabled }))?;
+
+    if enabled {
+        println!("Result caching enabled for dot {}", dot_id);
+    } else {
+        println!("Result caching disabled for dot {} ({} cached results dropped)", dot_id, field(&response, "evictedEntries"));
+    }
+
+    Ok(())
+}
+
+fn percent(rate: &Value) -> String {
+    rate.as_f64().map_or_else(|| "-".to_string(), |rate| format!("{:.1}%", rate * 100.0))
+}
@@ -0,0 +1,62 @@
+use super::{CommandContext, call_vm_service, field};
+use crate::CheckpointsCommands;
+use anyhow::Result;
+use serde_json::{Value, json};
+
+const LIST_CHECKPOINTS_METHOD: &str = "vm_service.VmService/ListCheckpoints";
+const RESTORE_CHECKPOINT_METHOD: &str = "vm_service.VmService/RestoreCheckpoint";
+
+pub fn handle_checkpoints_command(ctx: &CommandContext, command: CheckpointsCommands) -> Result<()> {
+    match command {
+        CheckpointsCommands::List { category } => list_checkpoints(ctx, category.as_deref()),
+        CheckpointsCommands::Restore { checkpoint_id } => restore_checkpoint(ctx, &checkpoint_id),
+    }
+}
+
+fn list_checkpoints(ctx: &CommandContext, category: Option<&str>) -> Result<()> {
+    let response = call_vm_service(ctx, LIST_CHECKPOINTS_METHOD, &json!({ "category": category.unwrap_or_default() }))?;
+
+    let checkpoints = response["checkpoints"].as_array().cloned().unwrap_or_default();
+    if checkpoints.is_empty() {
+        println!("No checkpoints retained");
+        return Ok(());
+    }
+
+    println!("{:<40} {:<18} {:<36} {:<12} {:<36} {:>12}", "Checkpoint", "Category", "Subject", "Status", "Operation", "Created");
+    println!("{}", "-".repeat(159));
+    for checkpoint in &checkpoints {
+        println!(
+            "{:<40} {:<18} {:<36} {:<12} {:<36} {:>12}",
+            field(checkpoint, "checkpointId"),
+            field(checkpoint, "category"),
+            field(checkpoint, "subject"),
+            field(checkpoint, "status"),
+            field(checkpoint, "operationId"),
+            field(checkpoint, "createdAt")
+        );
+        if let Some(failure) = failure(checkpoint) {
+            println!("  verification failed: {}", failure);
+        }
+    }
+
+    Ok(())
+}
+
+fn restore_checkpoint(ctx: &CommandContext, checkpoint_id: &str) -> Result<()> {
+    let response = call_vm_service(ctx, RESTORE_CHECKPOINT_METHOD, &json!({ "checkpoint_id": checkpoint_id }))?;
+    let checkpoint = &response["checkpoint"];
+
+    println!(
+        "Restored checkpoint {} of {} ({}, taken for operation {})",
+        checkpoint_id,
+        field(checkpoint, "subject"),
+        field(checkpoint, "category"),
+        field(checkpoint, "operationId")
+    );
+
+    Ok(())
+}
+
+fn failure(checkpoint: &Value) -> Option<&str> {
+    checkpoint["failure"].as_str().filter(|failure| !failure.is_empty())
+}
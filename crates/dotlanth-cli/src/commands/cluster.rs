@@ -1,19 +1,57 @@
-use super::CommandContext;
+use super::{CommandContext, call_vm_service, field};
 use crate::ClusterCommands;
+use crate::health::{self, HealthState};
 use anyhow::Result;
+use serde_json::{Value, json};
+use std::time::Duration;
+
+const REPLICATION_STATUS_METHOD: &str = "database_service.DatabaseService/GetReplicationStatus";
+
+/// Transitions listed under "Recent Health Transitions"
+const RECENT_TRANSITIONS: usize = 10;
 
 pub fn handle_cluster_command(ctx: &CommandContext, command: ClusterCommands) -> Result<()> {
     match command {
-        ClusterCommands::Status => show_status(ctx),
+        ClusterCommands::Status { watch: false } => show_status(ctx),
+        ClusterCommands::Status { watch: true } => watch_status(ctx),
         ClusterCommands::Scale { count } => scale_cluster(ctx, count),
+        ClusterCommands::Replicas => show_replicas(ctx),
     }
 }
 
 pub fn show_status(ctx: &CommandContext) -> Result<()> {
+    health::probe_all(&ctx.database, &ctx.config.health)?;
+    print_status(ctx)
+}
+
+/// Probes on the configured interval until interrupted, printing transitions as they happen
+fn watch_status(ctx: &CommandContext) -> Result<()> {
+    let interval = Duration::from_millis(ctx.config.health.probe_interval_ms);
+    println!("Probing nodes every {} ms (Ctrl+C to stop)", interval.as_millis());
+
+    loop {
+        for (node_id, transition) in health::probe_all(&ctx.database, &ctx.config.health)? {
+            println!(
+                "[{}] node {}: {} -> {} ({})",
+                transition.at.format("%H:%M:%S"),
+                node_id,
+                transition.from.name(),
+                transition.to.name(),
+                transition.reason
+            );
+        }
+        println!();
+        print_status(ctx)?;
+        std::thread::sleep(interval);
+    }
+}
+
+fn print_status(ctx: &CommandContext) -> Result<()> {
     println!("Cluster Status");
     println!("==============");
 
-    let nodes = ctx.database.list_nodes()?;
+    let mut nodes = ctx.database.list_nodes()?;
+    nodes.sort_by(|a, b| a.id.cmp(&b.id));
     let deployments = ctx.database.list_deployments()?;
 
     let online_nodes = nodes.iter().filter(|n| matches!(n.status, crate::database::NodeStatus::Online)).count();
@@ -22,8 +60,11 @@ pub fn show_status(ctx: &CommandContext) -> Result<()> {
     let running_deployments = deployments.iter().filter(|d| matches!(d.status, crate::database::DeploymentStatus::Running)).count();
     let total_deployments = deployments.len();
 
+    let healthy_nodes = nodes.iter().filter(|n| n.health.state == HealthState::Healthy).count();
+    let draining_nodes = nodes.iter().filter(|n| n.draining).count();
+
     println!("Overview:");
-    println!("  Nodes: {}/{} online", online_nodes, total_nodes);
+    println!("  Nodes: {}/{} online, {} healthy, {} draining", online_nodes, total_nodes, healthy_nodes, draining_nodes);
     println!("  Deployments: {}/{} running", running_deployments, total_deployments);
 
     if total_nodes > 0 {
@@ -39,6 +80,48 @@ pub fn show_status(ctx: &CommandContext) -> Result<()> {
         }
     } else {
         println!("  Status: No nodes registered");
+        return Ok(());
+    }
+
+    println!();
+    println!("Nodes:");
+    println!("  {:<20} {:<30} {:<10} {:<10} {:<9} {:<20}", "ID", "Address", "Health", "Latency", "Failures", "Last Success");
+    for node in &nodes {
+        let latency = node.health.latency_ms.map(|ms| format!("{} ms", ms)).unwrap_or_else(|| "-".to_string());
+        let last_success = node.health.last_success.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_else(|| "never".to_string());
+        let state = if node.draining { format!("{}*", node.health.state.name()) } else { node.health.state.name().to_string() };
+        println!(
+            "  {:<20} {:<30} {:<10} {:<10} {:<9} {:<20}",
+            &node.id[..20.min(node.id.len())],
+            node.address,
+            state,
+            latency,
+            node.health.consecutive_failures,
+            last_success
+        );
+        if let Some(error) = &node.health.last_error {
+            println!("      last error: {}", error);
+        }
+    }
+    if draining_nodes > 0 {
+        println!("  * draining");
+    }
+
+    let mut transitions: Vec<_> = nodes.iter().flat_map(|n| n.health.transitions.iter().map(move |t| (&n.id, t))).collect();
+    transitions.sort_by(|a, b| b.1.at.cmp(&a.1.at));
+    if !transitions.is_empty() {
+        println!();
+        println!("Recent Health Transitions:");
+        for (node_id, transition) in transitions.into_iter().take(RECENT_TRANSITIONS) {
+            println!(
+                "  {} {:<20} {} -> {} ({})",
+                transition.at.format("%Y-%m-%d %H:%M:%S"),
+                &node_id[..20.min(node_id.len())],
+                transition.from.name(),
+                transition.to.name(),
+                transition.reason
+            );
+        }
     }
 
     Ok(())
@@ -59,3 +142,58 @@ fn scale_cluster(ctx: &CommandContext, target_count: u32) -> Result<()> {
 
     Ok(())
 }
+
+fn show_replicas(ctx: &CommandContext) -> Result<()> {
+    let status = call_vm_service(ctx, REPLICATION_STATUS_METHOD, &json!({}))?;
+    let role = field(&status, "role");
+    match role.as_str() {
+        "replica" => {
+            let state = if status["connected"].as_bool().unwrap_or(false) { "streaming" } else { "reconnecting" };
+            println!(
+                "This node is a read replica of {} ({}, applied sequence {})",
+                field(&status, "primaryAddress"),
+                state,
+                field(&status, "sequence")
+            );
+            println!("Run this command against the primary to see every replica's lag.");
+            return Ok(());
+        }
+        "primary" => {}
+        _ => {
+            println!("This node does not replicate its documents");
+            return Ok(());
+        }
+    }
+
+    println!("Primary sequence: {}", field(&status, "sequence"));
+    let replicas = status["replicas"].as_array().map(Vec::as_slice).unwrap_or_default();
+    if replicas.is_empty() {
+        println!("No replicas have connected");
+        return Ok(());
+    }
+
+    println!();
+    println!("  {:<24} {:<24} {:<13} {:>10} {:>11} {:<20}", "Replica", "Address", "State", "Lag (rec)", "Lag (s)", "Last Seen");
+    for replica in replicas {
+        let state = if replica["connected"].as_bool().unwrap_or(false) { "streaming" } else { "disconnected" };
+        let lag_seconds = replica["lagSeconds"].as_f64().unwrap_or_default();
+        println!(
+            "  {:<24} {:<24} {:<13} {:>10} {:>11.1} {:<20}",
+            field(replica, "replicaId"),
+            field(replica, "address"),
+            state,
+            field(replica, "lagRecords"),
+            lag_seconds,
+            last_seen(replica)
+        );
+    }
+    Ok(())
+}
+
+fn last_seen(replica: &Value) -> String {
+    let millis = field(replica, "lastSeenMs").parse::<i64>().unwrap_or_default();
+    chrono::DateTime::from_timestamp_millis(millis)
+        .filter(|_| millis > 0)
+        .map(|at| at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| "never".to_string())
+}
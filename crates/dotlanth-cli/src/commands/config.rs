@@ -1,12 +1,45 @@
-use super::CommandContext;
+use super::{CommandContext, call_vm_service, field};
 use crate::ConfigCommands;
-use anyhow::Result;
+use crate::config::{ConfigOrigin, DotLanthConfig};
+use anyhow::{Result, bail};
+use serde_json::json;
+use std::path::Path;
+
+const UPDATE_STORAGE_CONFIG_METHOD: &str = "database_service.DatabaseService/UpdateStorageConfig";
 
 pub fn handle_config_command(ctx: &CommandContext, command: ConfigCommands) -> Result<()> {
     match command {
-        ConfigCommands::Show => show_config(ctx),
-        ConfigCommands::Set { key, value } => set_config(ctx, &key, &value),
+        ConfigCommands::Show { origins: true } => show_origins(ctx),
+        ConfigCommands::Show { origins: false } => show_config(ctx),
+        ConfigCommands::Set { key, value, live: true } => set_live_config(ctx, &key, &value),
+        ConfigCommands::Set { key, value, live: false } => set_config(ctx, &key, &value),
+        // Dispatched before the command context is built so a broken config can still be checked
+        ConfigCommands::Validate { path } => match path {
+            Some(path) => validate_config(&path),
+            None => Err(anyhow::anyhow!("No config file given")),
+        },
+    }
+}
+
+/// Checks a config file without loading anything else; exits with an error listing every problem
+pub fn validate_config(path: &Path) -> Result<()> {
+    let resolved = DotLanthConfig::validate_file(path)?;
+    let from_file = resolved.origins.values().filter(|o| matches!(o, ConfigOrigin::File { .. })).count();
+    println!("{} is valid ({} keys set, {} defaulted)", path.display(), from_file, resolved.origins.len() - from_file);
+    Ok(())
+}
+
+fn show_origins(ctx: &CommandContext) -> Result<()> {
+    let values = ctx.config.effective_values(&ctx.config_origins);
+    let width = values.iter().map(|(key, _, _)| key.len()).max().unwrap_or(0);
+
+    println!("Effective Configuration (CLI flags > environment > config file > defaults)");
+    println!("{}", "=".repeat(74));
+    for (key, value, origin) in values {
+        println!("{:<width$}  {:<32}  {}", key, value, origin, width = width);
     }
+
+    Ok(())
 }
 
 fn show_config(ctx: &CommandContext) -> Result<()> {
@@ -21,6 +54,7 @@ fn show_config(ctx: &CommandContext) -> Result<()> {
     println!("  Refresh Rate: {}ms", ctx.config.ui.refresh_rate_ms);
     println!("  Debug Info: {}", ctx.config.ui.show_debug_info);
     println!("  Max Log Lines: {}", ctx.config.ui.max_log_lines);
+    println!("  Log Buffer Size: {}", ctx.config.ui.log_buffer_size);
     println!();
 
     println!("Mock Data Settings:");
@@ -28,10 +62,49 @@ fn show_config(ctx: &CommandContext) -> Result<()> {
     println!("  Node Count: {}", ctx.config.mock_data.node_count);
     println!("  Deployment Count: {}", ctx.config.mock_data.deployment_count);
     println!("  Simulate Failures: {}", ctx.config.mock_data.simulate_failures);
+    println!();
+
+    println!("Health Probing:");
+    println!("  Probe Interval: {}ms", ctx.config.health.probe_interval_ms);
+    println!("  Probe Timeout: {}ms", ctx.config.health.probe_timeout_ms);
+    println!("  Failure Threshold: {}", ctx.config.health.failure_threshold);
+    println!();
+
+    println!("gRPC Settings:");
+    println!("  Client Address: {}:{}", ctx.config.grpc.client_host, ctx.config.grpc.client_port);
+    println!("  Connection Timeout: {}ms", ctx.config.grpc.connection_timeout_ms);
+    println!("  Auth Token: {}", if ctx.config.grpc.auth_token.is_some() { "********" } else { "(not set)" });
+    println!(
+        "  Transport: {}",
+        match (ctx.config.grpc.uses_tls(), ctx.config.grpc.insecure) {
+            (true, _) => "TLS",
+            (false, true) => "plaintext (--insecure)",
+            (false, false) => "plaintext",
+        }
+    );
 
     Ok(())
 }
 
+/// Applies a storage setting to the running node; boot-only settings are refused by the node
+fn set_live_config(ctx: &CommandContext, key: &str, value: &str) -> Result<()> {
+    let Some(setting) = key.strip_prefix("storage.") else {
+        bail!("Only storage.* settings can be changed live");
+    };
+
+    let response = call_vm_service(ctx, UPDATE_STORAGE_CONFIG_METHOD, &json!({ "settings": { setting: value } }))?;
+    let changes = response["changes"].as_array().cloned().unwrap_or_default();
+    if changes.is_empty() {
+        println!("{} is already {}", key, value);
+        return Ok(());
+    }
+
+    for change in &changes {
+        println!("storage.{}: {} -> {}", field(change, "setting"), field(change, "old_value"), field(change, "new_value"));
+    }
+    Ok(())
+}
+
 fn set_config(_ctx: &CommandContext, key: &str, value: &str) -> Result<()> {
     println!("Setting configuration: {} = {}", key, value);
 
@@ -54,6 +127,17 @@ fn set_config(_ctx: &CommandContext, key: &str, value: &str) -> Result<()> {
                 return Err(anyhow::anyhow!("Invalid refresh rate: {}", value));
             }
         }
+        "ui.log_buffer_size" => {
+            if let Ok(size) = value.parse::<usize>() {
+                if size > 0 {
+                    println!("Log buffer size set to: {}", size);
+                } else {
+                    return Err(anyhow::anyhow!("Log buffer size must be greater than 0"));
+                }
+            } else {
+                return Err(anyhow::anyhow!("Invalid log buffer size: {}", value));
+            }
+        }
         "ui.show_debug_info" => {
             if let Ok(debug) = value.parse::<bool>() {
                 println!("Debug info set to: {}", debug);
@@ -61,6 +145,28 @@ fn set_config(_ctx: &CommandContext, key: &str, value: &str) -> Result<()> {
                 return Err(anyhow::anyhow!("Invalid boolean value: {}", value));
             }
         }
+        "health.probe_interval_ms" | "health.probe_timeout_ms" => {
+            if let Ok(ms) = value.parse::<u64>() {
+                if ms >= 100 {
+                    println!("{} set to: {}ms", key, ms);
+                } else {
+                    return Err(anyhow::anyhow!("{} must be at least 100ms", key));
+                }
+            } else {
+                return Err(anyhow::anyhow!("Invalid duration: {}", value));
+            }
+        }
+        "health.failure_threshold" => {
+            if let Ok(threshold) = value.parse::<u32>() {
+                if threshold > 0 {
+                    println!("Failure threshold set to: {}", threshold);
+                } else {
+                    return Err(anyhow::anyhow!("Failure threshold must be greater than 0"));
+                }
+            } else {
+                return Err(anyhow::anyhow!("Invalid failure threshold: {}", value));
+            }
+        }
         "mock_data.generate_sample_data" => {
             if let Ok(generate) = value.parse::<bool>() {
                 println!("Sample data generation set to: {}", generate);
@@ -1,61 +1,267 @@
-use super::CommandContext;
-use crate::database::{DeploymentInfo, DeploymentStatus};
-use anyhow::Result;
-use std::path::Path;
+use super::{CommandContext, call_vm_service};
+use crate::database::DeploymentStatus;
+use crate::deployment::{DeploymentProgress, DeploymentRequest, DeploymentStage, DotDependency, StageState, parse_dependencies, run_deployment};
+use anyhow::{Context, Result, bail};
+use dotvm_core::bytecode::VmArchitecture;
+use dotvm_core::dots::{DependencyGraph, DependencyType};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
 
-pub fn deploy_dot(ctx: &CommandContext, dot_file: &Path) -> Result<()> {
-    println!("Deploying dot file: {}", dot_file.display());
+const DELETE_DOT_METHOD: &str = "vm_service.VmService/DeleteDot";
+const GET_ARCHITECTURES_METHOD: &str = "vm_service.VmService/GetArchitectures";
+
+#[derive(Debug, Clone, Copy)]
+pub struct DeployOptions {
+    pub rollback_on_failure: bool,
+    pub dry_run: bool,
+    pub failover: bool,
+    /// Architecture the dots are transpiled for
+    pub architecture: VmArchitecture,
+}
+
+/// Deploys a single dot file, or every `.dot` file in a directory in dependency order
+pub fn deploy(ctx: &CommandContext, path: &Path, options: DeployOptions) -> Result<()> {
+    if !options.dry_run {
+        warn_on_architecture_mismatch(ctx, options.architecture);
+    }
+
+    if path.is_dir() {
+        deploy_directory(ctx, path, options)
+    } else {
+        deploy_dot(ctx, path, options).map(|_| ())
+    }
+}
+
+/// Deploys one dot file, returning the time its stages took in milliseconds
+fn deploy_dot(ctx: &CommandContext, dot_file: &Path, options: DeployOptions) -> Result<u64> {
+    if options.dry_run {
+        println!("Validating dot file (dry run): {}", dot_file.display());
+    } else {
+        println!("Deploying dot file: {}", dot_file.display());
+    }
 
     // Check if file exists
     if !dot_file.exists() {
         return Err(anyhow::anyhow!("Dot file not found: {}", dot_file.display()));
     }
 
-    // Get available nodes
-    let nodes = ctx.database.list_nodes()?;
-    let online_nodes: Vec<_> = nodes.iter().filter(|n| matches!(n.status, crate::database::NodeStatus::Online)).collect();
+    let request = DeploymentRequest {
+        deployment_id: format!("deploy-{}", &uuid::Uuid::new_v4().to_string()[..8]),
+        dot_name: dot_file.file_stem().and_then(|s| s.to_str()).unwrap_or("unknown").to_string(),
+        dot_file: dot_file.to_path_buf(),
+        architecture: options.architecture,
+        dry_run: options.dry_run,
+        rollback_on_failure: options.rollback_on_failure,
+        failover: options.failover,
+        health: ctx.config.health.clone(),
+        grpc: ctx.config.grpc.clone(),
+    };
+
+    println!("  ID: {}", request.deployment_id);
+    println!("  Dot: {}", request.dot_name);
+    println!("  Architecture: {}", request.architecture);
+
+    let total = DeploymentStage::ALL.len();
+    let mut progress = DeploymentProgress::default();
+    let mut abi = None;
+
+    // The pipeline reports over a channel; print each update as it arrives
+    std::thread::scope(|scope| {
+        let (tx, rx) = mpsc::channel();
+        scope.spawn(|| run_deployment(&ctx.database, &request, tx));
 
-    if online_nodes.is_empty() {
-        return Err(anyhow::anyhow!("No online nodes available for deployment"));
+        for update in rx {
+            progress.apply(&update);
+            let step = format!("[{}/{}]", update.stage.index() + 1, total);
+            let note = update.message.as_deref().map(|m| format!(" - {}", m)).unwrap_or_default();
+            match &update.state {
+                StageState::Running => println!("{} {}...", step, update.stage.name()),
+                StageState::Completed => println!("{} {} done ({} ms){}", step, update.stage.name(), update.duration_ms.unwrap_or(0), note),
+                StageState::Failed(reason) => println!("{} {} FAILED ({} ms): {}{}", step, update.stage.name(), update.duration_ms.unwrap_or(0), reason, note),
+                StageState::Skipped => println!("{} {} skipped{}", step, update.stage.name(), note),
+                StageState::Pending => {}
+            }
+            if update.abi.is_some() {
+                abi = update.abi;
+            }
+        }
+    });
+
+    if let Some(failed) = progress.failed_stage() {
+        let reason = match &failed.state {
+            StageState::Failed(reason) => reason.clone(),
+            _ => String::new(),
+        };
+        return Err(anyhow::anyhow!("Deployment failed at stage '{}': {}", failed.stage.name(), reason));
     }
 
-    // Select first online node (simple strategy)
-    let target_node = &online_nodes[0];
+    if options.dry_run {
+        println!("Dry run complete. Generated ABI:");
+        println!("{}", serde_json::to_string_pretty(&abi.unwrap_or_default())?);
+        return Ok(progress.total_duration_ms());
+    }
 
-    // Create deployment
-    let deployment = DeploymentInfo {
-        id: format!("deploy-{}", uuid::Uuid::new_v4().to_string()[..8].to_string()),
-        dot_name: dot_file.file_stem().and_then(|s| s.to_str()).unwrap_or("unknown").to_string(),
-        dot_version: "1.0.0".to_string(),
-        node_id: target_node.id.clone(),
-        status: DeploymentStatus::Pending,
-        created_at: chrono::Utc::now(),
-        updated_at: chrono::Utc::now(),
-        config: serde_json::json!({
-            "file_path": dot_file.to_string_lossy(),
-            "memory": "512MB",
-            "cpu": "0.5"
-        }),
-    };
+    println!("Deployment successful in {} ms! Status: Running", progress.total_duration_ms());
+
+    Ok(progress.total_duration_ms())
+}
+
+/// A dot file found in the directory being deployed
+struct PlannedDot {
+    file: PathBuf,
+    dependencies: Vec<DotDependency>,
+}
 
-    ctx.database.create_deployment(deployment.clone())?;
+enum Outcome {
+    Deployed(u64),
+    Failed(String),
+    /// Not attempted because a dependency was not deployed
+    Skipped(Vec<String>),
+}
+
+/// Deploys the `.dot` files in `dir` so each dot goes out after the dots it requires.
+///
+/// Requirements outside the directory must already be running. A dot whose dependency fails is
+/// skipped; the others still deploy. Ends with a summary table.
+fn deploy_directory(ctx: &CommandContext, dir: &Path, options: DeployOptions) -> Result<()> {
+    let mut dots = BTreeMap::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let file = entry?.path();
+        if file.extension().is_none_or(|ext| ext != "dot") || !file.is_file() {
+            continue;
+        }
+        let name = file.file_stem().and_then(|s| s.to_str()).unwrap_or("unknown").to_string();
+        let source = std::fs::read_to_string(&file).with_context(|| format!("Failed to read {}", file.display()))?;
+        let dependencies = parse_dependencies(&source);
+        dots.insert(name, PlannedDot { file, dependencies });
+    }
+    if dots.is_empty() {
+        bail!("No .dot files found in {}", dir.display());
+    }
+
+    // The same graph the dot processor orders segments with, over whole dots
+    let running: HashSet<String> = ctx
+        .database
+        .list_deployments()?
+        .into_iter()
+        .filter(|d| matches!(d.status, DeploymentStatus::Running))
+        .map(|d| d.dot_name)
+        .collect();
+    let mut graph = DependencyGraph::new();
+    let mut missing = Vec::new();
+    for (name, dot) in &dots {
+        graph.add_segment(name);
+        for dependency in &dot.dependencies {
+            if dots.contains_key(&dependency.dot_name) {
+                graph.add_dependency(name, &dependency.dot_name, DependencyType::Prerequisite);
+            } else if !running.contains(&dependency.dot_name) {
+                missing.push(format!("{} requires {}", name, dependency));
+            }
+        }
+    }
+    if let Some(cycle) = graph.find_cycle() {
+        bail!("Dependency cycle: {}", cycle.join(" -> "));
+    }
+    if !missing.is_empty() {
+        bail!("Dependencies neither in {} nor running: {}", dir.display(), missing.join("; "));
+    }
+    let order = graph.topological_sort().map_err(|e| anyhow::anyhow!(e.to_string()))?;
+
+    println!("Deploying {} dots from {} in order: {}", order.len(), dir.display(), order.join(", "));
+    let mut outcomes: HashMap<&str, Outcome> = HashMap::new();
+    for name in &order {
+        let dot = &dots[name];
+        let blocked: Vec<String> = dot
+            .dependencies
+            .iter()
+            .filter(|d| dots.contains_key(&d.dot_name) && !matches!(outcomes.get(d.dot_name.as_str()), Some(Outcome::Deployed(_))))
+            .map(|d| d.dot_name.clone())
+            .collect();
+
+        println!();
+        let outcome = if !blocked.is_empty() {
+            println!("Skipping {}: {} not deployed", name, blocked.join(", "));
+            Outcome::Skipped(blocked)
+        } else {
+            match deploy_dot(ctx, &dot.file, options) {
+                Ok(duration_ms) => Outcome::Deployed(duration_ms),
+                Err(e) => {
+                    println!("{}", e);
+                    Outcome::Failed(e.to_string())
+                }
+            }
+        };
+        outcomes.insert(name, outcome);
+    }
 
-    println!("Deployment created:");
-    println!("  ID: {}", deployment.id);
-    println!("  Dot: {}", deployment.dot_name);
-    println!("  Target Node: {} ({})", target_node.id, target_node.address);
-    println!("  Status: Pending");
+    println!();
+    println!("{:<4} {:<20} {:<30} {:<10} {:>10}  Detail", "#", "Dot", "Depends on", "Status", "Time (ms)");
+    println!("{}", "-".repeat(90));
+    for (i, name) in order.iter().enumerate() {
+        let depends_on = dots[name].dependencies.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ");
+        let (status, time, detail) = match &outcomes[name.as_str()] {
+            Outcome::Deployed(ms) => (if options.dry_run { "validated" } else { "deployed" }, ms.to_string(), String::new()),
+            Outcome::Failed(reason) => ("failed", "-".to_string(), reason.clone()),
+            Outcome::Skipped(blocked) => ("skipped", "-".to_string(), format!("needs {}", blocked.join(", "))),
+        };
+        let row = format!(
+            "{:<4} {:<20} {:<30} {:<10} {:>10}  {}",
+            i + 1,
+            name,
+            if depends_on.is_empty() { "-" } else { &depends_on },
+            status,
+            time,
+            detail
+        );
+        println!("{}", row.trim_end());
+    }
 
-    // Simulate deployment process
-    println!("Uploading dot file...");
-    std::thread::sleep(std::time::Duration::from_millis(500));
+    let undeployed = outcomes.values().filter(|o| !matches!(o, Outcome::Deployed(_))).count();
+    if undeployed > 0 {
+        bail!("{} of {} dots were not deployed", undeployed, order.len());
+    }
+    Ok(())
+}
 
-    println!("Configuring runtime...");
-    std::thread::sleep(std::time::Duration::from_millis(300));
+/// Warns when the runtime's default architecture is not the one the dots are transpiled for.
+/// The runtime still runs them if it supports their architecture; a runtime that cannot be
+/// asked is not reported.
+fn warn_on_architecture_mismatch(ctx: &CommandContext, target: VmArchitecture) {
+    let Ok(response) = call_vm_service(ctx, GET_ARCHITECTURES_METHOD, &json!({})) else {
+        return;
+    };
+    if let Some(warning) = architecture_mismatch(&response, target) {
+        println!("Warning: {}", warning);
+    }
+}
 
-    // Update status to running
-    ctx.database.update_deployment_status(&deployment.id, DeploymentStatus::Running)?;
-    println!("Deployment successful! Status: Running");
+/// Why dots transpiled for `target` may not run as expected on the runtime that sent the
+/// GetArchitectures `response`, if they may not
+fn architecture_mismatch(response: &Value, target: VmArchitecture) -> Option<String> {
+    let architectures = response["architectures"].as_array()?;
+    let names: Vec<&str> = architectures.iter().filter_map(|architecture| architecture["name"].as_str()).collect();
+    let default = architectures.iter().find(|architecture| architecture["isDefault"].as_bool() == Some(true))?["name"].as_str()?;
 
+    let target_name = target.to_string();
+    if !names.contains(&target_name.as_str()) {
+        Some(format!("the runtime does not support {} (it supports {}); dots transpiled for it are rejected", target, names.join(", ")))
+    } else if default != target_name {
+        Some(format!("dots are transpiled for {}, but the runtime defaults to {}", target, default))
+    } else {
+        None
+    }
+}
+
+/// Deletes a deployed dot from the runtime. The runtime refuses while other dots depend on it
+/// unless `force` is set.
+pub fn delete_dot(ctx: &CommandContext, dot_id: &str, force: bool) -> Result<()> {
+    call_vm_service(ctx, DELETE_DOT_METHOD, &json!({ "dot_id": dot_id, "force": force }))?;
+    if force {
+        println!("Deleted dot {} (forced)", dot_id);
+    } else {
+        println!("Deleted dot {}", dot_id);
+    }
     Ok(())
 }
@@ -0,0 +1,128 @@
+use super::{CommandContext, call_vm_service, field};
+use crate::{LogsArgs, OutputFormat};
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Duration as ChronoDuration, Local, Utc};
+use serde_json::{Value, json};
+use std::time::Duration;
+
+const QUERY_LOGS_METHOD: &str = "vm_service.VmService/QueryLogs";
+
+/// Time between polls of the log store while following
+const FOLLOW_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Query the runtime's centralized log store, then keep printing new entries with `--follow`
+pub fn show_logs(ctx: &CommandContext, args: &LogsArgs) -> Result<()> {
+    let now = Utc::now();
+    let bound = |value: &Option<String>| value.as_deref().map(|value| parse_time(value, now)).transpose();
+    let mut request = json!({
+        "dot_id": args.dot.clone().unwrap_or_default(),
+        "level": args.level.clone().unwrap_or_default(),
+        // 64-bit integers go as strings, as proto3 JSON expects
+        "since_ns": bound(&args.since)?.unwrap_or_default().to_string(),
+        "until_ns": bound(&args.until)?.unwrap_or_default().to_string(),
+        "grep": args.grep.clone().unwrap_or_default(),
+        "limit": args.limit,
+    });
+
+    let response = call_vm_service(ctx, QUERY_LOGS_METHOD, &request)?;
+    let records = log_records(&response);
+    match (args.output, args.follow) {
+        (OutputFormat::Json, false) => println!("{}", serde_json::to_string_pretty(&Value::Array(records.iter().map(record_json).collect()))?),
+        (OutputFormat::Json, true) => records.iter().for_each(|record| println!("{}", record_json(record))),
+        (OutputFormat::Text, _) => {
+            if records.is_empty() && !args.follow {
+                println!("No matching log entries");
+            }
+            records.iter().for_each(|record| println!("{}", record_line(record)));
+        }
+    }
+    if !args.follow {
+        return Ok(());
+    }
+
+    // Tail by the order the store received entries, so late-shipped entries are not missed
+    let mut last_sequence = field(&response, "lastSequence");
+    loop {
+        request["after_sequence"] = json!(last_sequence);
+        let response = call_vm_service(ctx, QUERY_LOGS_METHOD, &request)?;
+        let records = log_records(&response);
+        for record in &records {
+            match args.output {
+                OutputFormat::Json => println!("{}", record_json(record)),
+                OutputFormat::Text => println!("{}", record_line(record)),
+            }
+        }
+        last_sequence = field(&response, "lastSequence");
+        // A full page means more entries are waiting
+        if records.len() < args.limit as usize {
+            std::thread::sleep(FOLLOW_INTERVAL);
+        }
+    }
+}
+
+fn log_records(response: &Value) -> Vec<Value> {
+    response["records"].as_array().cloned().unwrap_or_default()
+}
+
+fn timestamp(record: &Value) -> DateTime<Utc> {
+    let nanos = field(record, "timestampNs").parse::<i64>().unwrap_or_default();
+    DateTime::from_timestamp_nanos(nanos)
+}
+
+/// One entry as printed in text mode: time, level, node, dot, target and message, then fields
+fn record_line(record: &Value) -> String {
+    let dot_id = record["dotId"].as_str().filter(|dot_id| !dot_id.is_empty()).unwrap_or("-");
+    let mut line = format!(
+        "{} {:<5} {} {} {}: {}",
+        timestamp(record).with_timezone(&Local).format("%Y-%m-%d %H:%M:%S%.3f"),
+        field(record, "level"),
+        field(record, "node"),
+        dot_id,
+        field(record, "target"),
+        field(record, "message")
+    );
+    for (name, value) in record["fields"].as_object().into_iter().flatten() {
+        line.push_str(&format!(" {}={}", name, value.as_str().unwrap_or_default()));
+    }
+    line
+}
+
+/// One entry in JSON output, with snake_case keys like dotdb's JSON output
+fn record_json(record: &Value) -> Value {
+    json!({
+        "timestamp": timestamp(record).to_rfc3339(),
+        "timestamp_ns": field(record, "timestampNs").parse::<u64>().unwrap_or_default(),
+        "level": field(record, "level"),
+        "node": field(record, "node"),
+        "service": field(record, "service"),
+        "dot_id": record["dotId"].as_str().filter(|dot_id| !dot_id.is_empty()),
+        "target": field(record, "target"),
+        "message": field(record, "message"),
+        "fields": record["fields"].clone(),
+        "sequence": field(record, "sequence").parse::<u64>().unwrap_or_default(),
+    })
+}
+
+/// Nanoseconds since the Unix epoch of an RFC 3339 time, or of an age such as `90s`, `15m`,
+/// `2h` or `1d` before `now`
+fn parse_time(value: &str, now: DateTime<Utc>) -> Result<u64> {
+    let time = match DateTime::parse_from_rfc3339(value) {
+        Ok(time) => time.with_timezone(&Utc),
+        Err(_) => {
+            let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+            let (amount, unit) = value.split_at(split);
+            let amount: i64 = amount.parse().with_context(|| format!("Invalid time '{}': expected RFC 3339 or an age such as 15m", value))?;
+            let age = match unit {
+                "s" => ChronoDuration::seconds(amount),
+                "m" => ChronoDuration::minutes(amount),
+                "h" => ChronoDuration::hours(amount),
+                "d" => ChronoDuration::days(amount),
+                _ => bail!("Invalid time '{}': an age needs a unit of s, m, h or d", value),
+            };
+            now - age
+        }
+    };
+    time.timestamp_nanos_opt()
+        .and_then(|nanos| u64::try_from(nanos).ok())
+        .with_context(|| format!("Time '{}' is out of range", value))
+}
@@ -0,0 +1,130 @@
+use super::{CommandContext, call_vm_service, field};
+use crate::MemoryCommands;
+use anyhow::{Result, bail};
+use serde_json::{Value, json};
+
+const GET_METRICS_METHOD: &str = "vm_service.VmService/GetVMMetrics";
+const SET_TRACKING_METHOD: &str = "vm_service.VmService/SetMemoryTracking";
+
+const LIVE_BYTES_METRIC: &str = "dot_memory_live_bytes";
+const LIVE_ALLOCATIONS_METRIC: &str = "dot_memory_live_allocations";
+const LEAK_SUSPECT_METRIC: &str = "memory_leak_suspect_bytes";
+
+pub fn handle_memory_command(ctx: &CommandContext, command: MemoryCommands) -> Result<()> {
+    match command {
+        MemoryCommands::Usage { dot_id } => show_usage(ctx, dot_id.as_deref()),
+        MemoryCommands::Leaks { dot_id, show_sites } => show_leaks(ctx, dot_id.as_deref(), show_sites),
+        MemoryCommands::Tracking {
+            enable,
+            disable,
+            backtraces,
+            leak_age_secs,
+        } => set_tracking(ctx, enable, disable, backtraces, leak_age_secs),
+    }
+}
+
+fn show_usage(ctx: &CommandContext, dot_id: Option<&str>) -> Result<()> {
+    let metrics = fetch_metrics(ctx, &[LIVE_BYTES_METRIC, LIVE_ALLOCATIONS_METRIC])?;
+
+    println!("{:<36} {:>14} {:>12}", "Dot", "Live bytes", "Allocations");
+    println!("{}", "-".repeat(64));
+    let mut rows = 0;
+    for metric in metrics.iter().filter(|m| m["name"] == LIVE_BYTES_METRIC) {
+        let dot = label(metric, "dot_id");
+        if dot_id.is_some_and(|id| id != dot) {
+            continue;
+        }
+        let allocations = metrics
+            .iter()
+            .find(|m| m["name"] == LIVE_ALLOCATIONS_METRIC && label(m, "dot_id") == dot)
+            .map_or_else(|| "-".to_string(), latest_value);
+        println!("{:<36} {:>14} {:>12}", dot, latest_value(metric), allocations);
+        rows += 1;
+    }
+    if rows == 0 {
+        println!("No tracked allocations (is memory tracking enabled?)");
+    }
+
+    Ok(())
+}
+
+fn show_leaks(ctx: &CommandContext, dot_id: Option<&str>, show_sites: bool) -> Result<()> {
+    let metrics = fetch_metrics(ctx, &[LEAK_SUSPECT_METRIC])?;
+    let suspects: Vec<&Value> = metrics
+        .iter()
+        .filter(|m| m["name"] == LEAK_SUSPECT_METRIC)
+        .filter(|m| dot_id.is_none_or(|id| label(m, "dot_id") == id))
+        .collect();
+
+    let Some(first) = suspects.first() else {
+        println!("No allocations older than the configured leak age");
+        return Ok(());
+    };
+
+    println!("Allocations older than {}s", label(first, "leak_age_secs"));
+    println!("{:<36} {:>18} {:>12} {:>10}", "Dot", "Handle", "Bytes", "Age (s)");
+    println!("{}", "-".repeat(79));
+    for suspect in &suspects {
+        println!(
+            "{:<36} {:>18} {:>12} {:>10}",
+            label(suspect, "dot_id"),
+            label(suspect, "handle"),
+            latest_value(suspect),
+            label(suspect, "age_secs")
+        );
+        if show_sites {
+            match suspect["labels"]["site"].as_str() {
+                Some(site) => println!("{}", site),
+                None => println!("  (no allocation site; enable backtraces with `dotlanth memory tracking --backtraces on`)"),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn set_tracking(ctx: &CommandContext, enable: bool, disable: bool, backtraces: Option<bool>, leak_age_secs: Option<u64>) -> Result<()> {
+    if !enable && !disable && backtraces.is_none() && leak_age_secs.is_none() {
+        bail!("Specify at least one of --enable, --disable, --backtraces or --leak-age-secs");
+    }
+    if leak_age_secs == Some(0) {
+        bail!("--leak-age-secs must be greater than zero");
+    }
+
+    // Unspecified settings keep their current values
+    let current = call_vm_service(ctx, SET_TRACKING_METHOD, &json!({}))?;
+    let config = &current["config"];
+    let enabled = if enable || disable { Value::from(enable) } else { config["enabled"].clone() };
+    let request = json!({
+        "config": {
+            "enabled": enabled,
+            "capture_backtraces": backtraces.map_or_else(|| config["captureBacktraces"].clone(), Value::from),
+            "leak_age_secs": leak_age_secs.map_or_else(|| config["leakAgeSecs"].clone(), Value::from),
+        }
+    });
+
+    let response = call_vm_service(ctx, SET_TRACKING_METHOD, &request)?;
+    let config = &response["config"];
+    println!("Memory tracking updated:");
+    println!("  Enabled: {}", field(config, "enabled"));
+    println!("  Backtraces: {}", field(config, "captureBacktraces"));
+    println!("  Leak age: {}s", field(config, "leakAgeSecs"));
+
+    Ok(())
+}
+
+fn fetch_metrics(ctx: &CommandContext, names: &[&str]) -> Result<Vec<Value>> {
+    let response = call_vm_service(ctx, GET_METRICS_METHOD, &json!({ "metric_names": names }))?;
+    Ok(response["metrics"].as_array().cloned().unwrap_or_default())
+}
+
+fn label<'a>(metric: &'a Value, key: &str) -> &'a str {
+    metric["labels"][key].as_str().unwrap_or("-")
+}
+
+fn latest_value(metric: &Value) -> String {
+    metric["dataPoints"]
+        .as_array()
+        .and_then(|points| points.last())
+        .map_or_else(|| "-".to_string(), |point| field(point, "value"))
+}
@@ -1,25 +1,154 @@
+pub mod apikey;
 pub mod backup;
+pub mod cache;
+pub mod checkpoints;
 pub mod cluster;
 pub mod config;
 pub mod deploy;
+pub mod logs;
+pub mod memory;
 pub mod monitor;
+pub mod new;
 pub mod nodes;
+pub mod quota;
+pub mod test_session;
 
-use crate::config::DotLanthConfig;
+use crate::config::{ConfigOrigin, DotLanthConfig, GrpcConfig, ResolvedConfig};
 use crate::database::DotLanthDatabase;
-use anyhow::Result;
+use anyhow::{Context, Result, bail};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::BufReader;
+use std::process::{Command, Stdio};
 
 pub struct CommandContext {
     pub config: DotLanthConfig,
+    /// Where each effective config value came from, keyed by dotted path
+    pub config_origins: BTreeMap<String, ConfigOrigin>,
     pub database: DotLanthDatabase,
 }
 
 impl CommandContext {
-    pub fn new(config: DotLanthConfig) -> Result<Self> {
-        let database = DotLanthDatabase::new(&config.data_dir.join("mock_db"))?;
+    pub fn new(resolved: ResolvedConfig) -> Result<Self> {
+        let ResolvedConfig { config, origins } = resolved;
+        let database = DotLanthDatabase::new(&config.data_dir.join("mock_db"), config.ui.log_buffer_size)?;
         if config.mock_data.generate_sample_data {
             database.generate_sample_data()?;
         }
-        Ok(Self { config, database })
+        Ok(Self {
+            config,
+            config_origins: origins,
+            database,
+        })
+    }
+}
+
+/// Invokes a VmService method through grpcurl and returns the JSON response
+pub(crate) fn call_vm_service(ctx: &CommandContext, method: &str, request: &Value) -> Result<Value> {
+    let grpc_addr = format!("{}:{}", ctx.config.grpc.client_host, ctx.config.grpc.client_port);
+    call_vm_service_at(&ctx.config.grpc, &grpc_addr, method, request)
+}
+
+/// Invokes a VmService method on the runtime at `grpc_addr` with the configured transport and credentials
+pub(crate) fn call_vm_service_at(grpc: &GrpcConfig, grpc_addr: &str, method: &str, request: &Value) -> Result<Value> {
+    let timeout_secs = (grpc.connection_timeout_ms / 1000).max(1).to_string();
+
+    let mut cmd = grpcurl(grpc);
+    cmd.args(["-max-time", &timeout_secs]);
+    cmd.args(["-d", &request.to_string(), grpc_addr, method]);
+
+    let output = cmd.output().context("Failed to run grpcurl; is it installed and on PATH?")?;
+    if !output.status.success() {
+        bail!("{} failed: {}", method, String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    serde_json::from_slice(&output.stdout).with_context(|| format!("Invalid response from {}", method))
+}
+
+/// Invokes a server-streaming VmService method on the runtime at `grpc_addr`, handing each
+/// response to `on_message` as it arrives. Only connecting is bounded by the timeout.
+pub(crate) fn stream_vm_service_at(grpc: &GrpcConfig, grpc_addr: &str, method: &str, request: &Value, mut on_message: impl FnMut(Value) -> Result<()>) -> Result<()> {
+    let timeout_secs = (grpc.connection_timeout_ms / 1000).max(1).to_string();
+
+    let mut cmd = grpcurl(grpc);
+    cmd.args(["-connect-timeout", &timeout_secs]);
+    cmd.args(["-d", &request.to_string(), grpc_addr, method]);
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+    let mut child = cmd.spawn().context("Failed to run grpcurl; is it installed and on PATH?")?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    // grpcurl prints the responses one JSON document after another
+    for message in serde_json::Deserializer::from_reader(BufReader::new(stdout)).into_iter::<Value>() {
+        if let Err(e) = message.with_context(|| format!("Invalid response from {}", method)).and_then(&mut on_message) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(e);
+        }
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!("{} failed: {}", method, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+/// A grpcurl command with the configured transport and credentials
+fn grpcurl(grpc: &GrpcConfig) -> Command {
+    let mut cmd = Command::new("grpcurl");
+    cmd.args(grpc.grpcurl_transport_args());
+    cmd.arg("-emit-defaults");
+    if let Some(token) = grpc.auth_token.clone().or_else(|| std::env::var("DOTLANTH_AUTH_TOKEN").ok()) {
+        cmd.args(["-H", &format!("Authorization: Bearer {}", token)]);
+    }
+    cmd
+}
+
+/// Calls the REST gateway through curl and returns the JSON response, failing with the
+/// problem detail on an error status
+pub(crate) fn call_gateway(ctx: &CommandContext, method: &str, path: &str, body: Option<&Value>) -> Result<Value> {
+    let gateway = &ctx.config.gateway;
+    let url = format!("{}{}", gateway.url.trim_end_matches('/'), path);
+    let timeout_secs = (ctx.config.grpc.connection_timeout_ms / 1000).max(1).to_string();
+
+    let mut cmd = Command::new("curl");
+    cmd.args(["--silent", "--show-error", "--max-time", &timeout_secs, "--request", method, "--write-out", "\n%{http_code}"]);
+    if let Some(token) = &gateway.auth_token {
+        cmd.args(["--header", &format!("Authorization: Bearer {}", token)]);
+    } else if let Some(api_key) = &gateway.api_key {
+        cmd.args(["--header", &format!("X-API-Key: {}", api_key)]);
+    }
+    if let Some(body) = body {
+        cmd.args(["--header", "Content-Type: application/json", "--data", &body.to_string()]);
+    }
+    cmd.arg(&url);
+
+    let output = cmd.output().context("Failed to run curl; is it installed and on PATH?")?;
+    if !output.status.success() {
+        bail!("{} {} failed: {}", method, url, String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    // The status code is written on the line after the body
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (body, status) = stdout.rsplit_once('\n').unwrap_or(("", &stdout));
+    let status: u16 = status.trim().parse().with_context(|| format!("No status code in response from {}", url))?;
+    if status >= 400 {
+        let problem: Value = serde_json::from_str(body).unwrap_or_default();
+        let detail = problem["detail"].as_str().map_or_else(|| body.trim().to_string(), str::to_string);
+        bail!("{} {} failed with status {}: {}", method, path, status, detail);
+    }
+    if body.trim().is_empty() {
+        return Ok(Value::Null);
+    }
+
+    serde_json::from_str(body).with_context(|| format!("Invalid response from {}", url))
+}
+
+/// Renders a response field, which proto3 JSON may encode as a number or a string
+pub(crate) fn field(value: &Value, key: &str) -> String {
+    match &value[key] {
+        Value::String(s) => s.clone(),
+        Value::Null => "-".to_string(),
+        other => other.to_string(),
     }
 }
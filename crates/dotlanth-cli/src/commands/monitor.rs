@@ -1,5 +1,6 @@
 use super::CommandContext;
-use anyhow::Result;
+use crate::exporter::{self, ExporterOptions, Target};
+use anyhow::{Result, bail};
 use std::time::Duration;
 
 pub fn start_monitoring(ctx: &CommandContext) -> Result<()> {
@@ -36,35 +37,33 @@ pub fn start_monitoring(ctx: &CommandContext) -> Result<()> {
     Ok(())
 }
 
-pub fn show_logs(ctx: &CommandContext) -> Result<()> {
-    println!("Recent System Logs");
-    println!("==================");
-
-    let logs = ctx.database.get_recent_logs(None, 20)?;
-
-    if logs.is_empty() {
-        println!("No logs available");
-        return Ok(());
+/// Serves the runtime's metrics for Prometheus at `options.listen`
+///
+/// Polls the configured runtime, or every registered node with `all_nodes`. With
+/// `echo` each poll is reported on the terminal as well.
+pub fn serve_metrics(ctx: &CommandContext, options: &ExporterOptions, all_nodes: bool, echo: bool) -> Result<()> {
+    if options.interval.is_zero() {
+        bail!("--interval-secs must be greater than zero");
     }
 
-    for log in logs {
-        let level_indicator = match log.level.as_str() {
-            "ERROR" => "[E]",
-            "WARN" => "[W]",
-            "INFO" => "[I]",
-            "DEBUG" => "[D]",
-            _ => "[?]",
-        };
-
-        println!(
-            "{} [{}] [{}] [{}] {}",
-            level_indicator,
-            log.timestamp.format("%H:%M:%S"),
-            log.level,
-            log.node_id.chars().take(8).collect::<String>(),
-            log.message
-        );
+    let targets = if all_nodes {
+        let mut nodes = ctx.database.list_nodes()?;
+        nodes.sort_by(|a, b| a.id.cmp(&b.id));
+        nodes
+            .into_iter()
+            .map(|node| Target {
+                address: node.address.trim_start_matches("http://").trim_start_matches("https://").to_string(),
+                node: node.id,
+            })
+            .collect()
+    } else {
+        let address = format!("{}:{}", ctx.config.grpc.client_host, ctx.config.grpc.client_port);
+        vec![Target { node: address.clone(), address }]
+    };
+    if targets.is_empty() {
+        bail!("No nodes registered; add one with `dotlanth nodes add` or drop --all-nodes");
     }
 
-    Ok(())
+    println!("Serving metrics of {} runtime(s) at http://{}/metrics (Ctrl+C to stop)", targets.len(), options.listen);
+    exporter::run(ctx.config.grpc.clone(), targets, options, echo)
 }
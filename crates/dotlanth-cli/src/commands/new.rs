@@ -0,0 +1,158 @@
+use crate::{DotTemplate, NewArgs};
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use std::path::{Path, PathBuf};
+
+/// Replaced with the dot name in every generated file
+const NAME_PLACEHOLDER: &str = "{{dot_name}}";
+
+/// A file of a template; its path may contain the name placeholder
+struct TemplateFile {
+    path: &'static str,
+    contents: &'static str,
+}
+
+/// Files every template shares
+const COMMON_FILES: &[TemplateFile] = &[
+    TemplateFile {
+        path: "Cargo.toml",
+        contents: include_str!("../../templates/common/Cargo.toml.tmpl"),
+    },
+    TemplateFile {
+        path: "justfile",
+        contents: include_str!("../../templates/common/justfile"),
+    },
+    TemplateFile {
+        path: ".gitignore",
+        contents: include_str!("../../templates/common/gitignore"),
+    },
+    TemplateFile {
+        path: "src/host.rs",
+        contents: include_str!("../../templates/common/src/host.rs"),
+    },
+];
+
+const BASIC_FILES: &[TemplateFile] = &[
+    TemplateFile {
+        path: "src/lib.rs",
+        contents: include_str!("../../templates/basic/src/lib.rs"),
+    },
+    TemplateFile {
+        path: "abi.json",
+        contents: include_str!("../../templates/basic/abi.json"),
+    },
+    TemplateFile {
+        path: "{{dot_name}}.dot",
+        contents: include_str!("../../templates/basic/manifest.dot"),
+    },
+];
+
+const STATEFUL_FILES: &[TemplateFile] = &[
+    TemplateFile {
+        path: "src/lib.rs",
+        contents: include_str!("../../templates/stateful/src/lib.rs"),
+    },
+    TemplateFile {
+        path: "abi.json",
+        contents: include_str!("../../templates/stateful/abi.json"),
+    },
+    TemplateFile {
+        path: "{{dot_name}}.dot",
+        contents: include_str!("../../templates/stateful/manifest.dot"),
+    },
+];
+
+const HTTP_HANDLER_FILES: &[TemplateFile] = &[
+    TemplateFile {
+        path: "src/lib.rs",
+        contents: include_str!("../../templates/http-handler/src/lib.rs"),
+    },
+    TemplateFile {
+        path: "abi.json",
+        contents: include_str!("../../templates/http-handler/abi.json"),
+    },
+    TemplateFile {
+        path: "{{dot_name}}.dot",
+        contents: include_str!("../../templates/http-handler/manifest.dot"),
+    },
+];
+
+fn template_files(template: DotTemplate) -> &'static [TemplateFile] {
+    match template {
+        DotTemplate::Basic => BASIC_FILES,
+        DotTemplate::Stateful => STATEFUL_FILES,
+        DotTemplate::HttpHandler => HTTP_HANDLER_FILES,
+    }
+}
+
+pub fn handle_new_command(args: &NewArgs) -> Result<()> {
+    if args.list_templates {
+        list_templates();
+        return Ok(());
+    }
+    let Some(name) = &args.name else {
+        bail!("No dot name given; pass a name or --list-templates");
+    };
+    let dir = args.path.clone().unwrap_or_else(|| PathBuf::from(name));
+
+    let written = generate(name, args.template, &dir, args.force)?;
+    println!("Created {} dot '{}' in {}", template_name(args.template), name, dir.display());
+    for path in &written {
+        println!("  {}", path);
+    }
+    println!();
+    println!("Next steps:");
+    println!("  cd {}", dir.display());
+    println!("  just        # transpile with dotvm, then dotlanth deploy --dry-run {}.dot", name);
+    Ok(())
+}
+
+fn template_name(template: DotTemplate) -> String {
+    template.to_possible_value().map(|value| value.get_name().to_string()).unwrap_or_default()
+}
+
+/// Prints each template with the description clap shows for it
+fn list_templates() {
+    let templates: Vec<_> = DotTemplate::value_variants().iter().filter_map(|template| template.to_possible_value()).collect();
+    let width = templates.iter().map(|value| value.get_name().len()).max().unwrap_or(0);
+    for value in templates {
+        let help = value.get_help().map(ToString::to_string).unwrap_or_default();
+        println!("{:<width$}  {}", value.get_name(), help, width = width);
+    }
+}
+
+/// Writes the project for `name` into `dir`, returning the paths written relative to it.
+///
+/// A directory that exists and is not empty is only written into with `force`, which
+/// overwrites the template's files and leaves any others alone.
+fn generate(name: &str, template: DotTemplate, dir: &Path, force: bool) -> Result<Vec<String>> {
+    validate_name(name)?;
+    let occupied = dir.is_dir() && std::fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?.next().is_some();
+    if occupied && !force {
+        bail!("{} is not empty; pass --force to generate into it anyway", dir.display());
+    }
+    if dir.exists() && !dir.is_dir() {
+        bail!("{} exists and is not a directory", dir.display());
+    }
+
+    let mut written = Vec::new();
+    for file in COMMON_FILES.iter().chain(template_files(template)) {
+        let relative = file.path.replace(NAME_PLACEHOLDER, name);
+        let path = dir.join(&relative);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        std::fs::write(&path, file.contents.replace(NAME_PLACEHOLDER, name)).with_context(|| format!("Failed to write {}", path.display()))?;
+        written.push(relative);
+    }
+    Ok(written)
+}
+
+/// Dot names become the Cargo package name and the name in the manifest
+fn validate_name(name: &str) -> Result<()> {
+    let valid_start = name.starts_with(|c: char| c.is_ascii_alphabetic());
+    if !valid_start || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        bail!("Invalid dot name '{}': use ASCII letters, digits, '-' and '_', starting with a letter", name);
+    }
+    Ok(())
+}
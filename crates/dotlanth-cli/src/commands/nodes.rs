@@ -1,6 +1,7 @@
 use super::CommandContext;
 use crate::NodeCommands;
 use crate::database::{NodeInfo, NodeStatus};
+use crate::health::{self, NodeHealth};
 use anyhow::Result;
 use serde_json::Value;
 
@@ -9,11 +10,14 @@ pub fn handle_node_command(ctx: &CommandContext, command: NodeCommands) -> Resul
         NodeCommands::List => list_nodes(ctx),
         NodeCommands::Add { addr } => add_node(ctx, &addr),
         NodeCommands::Remove { node_id } => remove_node(ctx, &node_id),
+        NodeCommands::Drain { node_id } => drain_node(ctx, &node_id),
     }
 }
 
 fn list_nodes(ctx: &CommandContext) -> Result<()> {
-    let nodes = ctx.database.list_nodes()?;
+    health::probe_all(&ctx.database, &ctx.config.health)?;
+    let mut nodes = ctx.database.list_nodes()?;
+    nodes.sort_by(|a, b| a.id.cmp(&b.id));
 
     if nodes.is_empty() {
         println!("No nodes registered.");
@@ -21,8 +25,8 @@ fn list_nodes(ctx: &CommandContext) -> Result<()> {
     }
 
     println!("Registered Nodes:");
-    println!("{:<20} {:<30} {:<12} {:<10} {:<20}", "ID", "Address", "Status", "Version", "Last Heartbeat");
-    println!("{}", "-".repeat(92));
+    println!("{:<20} {:<30} {:<12} {:<10} {:<10} {:<20}", "ID", "Address", "Status", "Health", "Version", "Last Heartbeat");
+    println!("{}", "-".repeat(103));
 
     for node in nodes {
         let status_str = match node.status {
@@ -32,11 +36,14 @@ fn list_nodes(ctx: &CommandContext) -> Result<()> {
             NodeStatus::Error(_) => "Error",
         };
 
+        let status_str = if node.draining { format!("{} (drain)", status_str) } else { status_str.to_string() };
+
         println!(
-            "{:<20} {:<30} {:<12} {:<10} {:<20}",
+            "{:<20} {:<30} {:<12} {:<10} {:<10} {:<20}",
             &node.id[..20.min(node.id.len())],
             node.address,
             status_str,
+            node.health.state.name(),
             node.version,
             node.last_heartbeat.format("%Y-%m-%d %H:%M:%S")
         );
@@ -54,6 +61,8 @@ fn add_node(ctx: &CommandContext, address: &str) -> Result<()> {
         version: "1.0.0".to_string(),
         capabilities: vec!["dotvm".to_string(), "dotdb".to_string()],
         metadata: Value::Object(serde_json::Map::new()),
+        health: NodeHealth::default(),
+        draining: false,
     };
 
     ctx.database.register_node(node.clone())?;
@@ -75,3 +84,16 @@ fn remove_node(ctx: &CommandContext, node_id: &str) -> Result<()> {
 
     Ok(())
 }
+
+fn drain_node(ctx: &CommandContext, node_id: &str) -> Result<()> {
+    if !ctx.database.set_node_draining(node_id, true)? {
+        println!("Node {} not found.", node_id);
+        return Ok(());
+    }
+
+    let deployments = ctx.database.list_deployments()?.into_iter().filter(|d| d.node_id == node_id).count();
+    println!("Node {} is draining; new deployments will not target it.", node_id);
+    println!("  {} existing deployment(s) left running", deployments);
+
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use super::{CommandContext, call_vm_service, field};
+use crate::QuotaCommands;
+use anyhow::{Result, bail};
+use serde_json::{Value, json};
+
+const SET_QUOTA_METHOD: &str = "vm_service.VmService/SetDotQuota";
+const GET_QUOTA_METHOD: &str = "vm_service.VmService/GetDotQuota";
+
+pub fn handle_quota_command(ctx: &CommandContext, command: QuotaCommands) -> Result<()> {
+    match command {
+        QuotaCommands::Get { dot_id } => get_quota(ctx, &dot_id),
+        QuotaCommands::Set {
+            dot_id,
+            memory_mb,
+            cpu_cores,
+            max_tasks,
+        } => set_quota(ctx, &dot_id, memory_mb, cpu_cores, max_tasks),
+    }
+}
+
+fn get_quota(ctx: &CommandContext, dot_id: &str) -> Result<()> {
+    let response = call_vm_service(ctx, GET_QUOTA_METHOD, &json!({ "dot_id": dot_id }))?;
+    let quota = &response["quota"];
+    let usage = &response["usage"];
+
+    println!("Quota for dot {}", dot_id);
+    println!("{:<18} {:>12} {:>12}", "Resource", "Used", "Limit");
+    println!("{}", "-".repeat(44));
+    println!("{:<18} {:>12} {:>12}", "Memory (MB)", field(usage, "memoryMb"), field(quota, "maxMemoryMb"));
+    println!("{:<18} {:>12} {:>12}", "CPU cores", field(usage, "cpuCores"), field(quota, "maxCpuCores"));
+    println!("{:<18} {:>12} {:>12}", "Concurrent tasks", field(usage, "activeTasks"), field(quota, "maxConcurrentTasks"));
+    println!();
+    println!("Rejected allocations: {}", field(&response, "rejections"));
+
+    Ok(())
+}
+
+fn set_quota(ctx: &CommandContext, dot_id: &str, memory_mb: Option<u64>, cpu_cores: Option<f32>, max_tasks: Option<u32>) -> Result<()> {
+    if memory_mb.is_none() && cpu_cores.is_none() && max_tasks.is_none() {
+        bail!("Specify at least one of --memory-mb, --cpu-cores or --max-tasks");
+    }
+
+    // Unspecified limits keep their current values
+    let current = call_vm_service(ctx, GET_QUOTA_METHOD, &json!({ "dot_id": dot_id }))?;
+    let quota = &current["quota"];
+    let request = json!({
+        "dot_id": dot_id,
+        "quota": {
+            "max_memory_mb": memory_mb.map_or_else(|| quota["maxMemoryMb"].clone(), Value::from),
+            "max_cpu_cores": cpu_cores.map_or_else(|| quota["maxCpuCores"].clone(), Value::from),
+            "max_concurrent_tasks": max_tasks.map_or_else(|| quota["maxConcurrentTasks"].clone(), Value::from),
+        }
+    });
+
+    let response = call_vm_service(ctx, SET_QUOTA_METHOD, &request)?;
+    let quota = &response["quota"];
+    println!("Quota updated for dot {}:", dot_id);
+    println!("  Memory: {} MB", field(quota, "maxMemoryMb"));
+    println!("  CPU cores: {}", field(quota, "maxCpuCores"));
+    println!("  Concurrent tasks: {}", field(quota, "maxConcurrentTasks"));
+
+    Ok(())
+}
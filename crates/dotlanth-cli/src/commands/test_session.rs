@@ -0,0 +1,93 @@
+use super::CommandContext;
+use crate::TestSessionCommands;
+use crate::grpc_tester::{self, RequestBodies, SessionStep, TestSessions};
+use anyhow::{Result, anyhow, bail};
+
+pub fn handle_test_session_command(ctx: &CommandContext, command: TestSessionCommands) -> Result<()> {
+    let mut sessions = TestSessions::load(&ctx.config.data_dir)?;
+    match command {
+        TestSessionCommands::Run { name } => run_session(ctx, &sessions, &name),
+        TestSessionCommands::List => list_sessions(&sessions),
+        TestSessionCommands::Show { name } => show_session(&sessions, &name),
+        TestSessionCommands::Add { name, endpoint, body } => add_step(ctx, &mut sessions, &name, &endpoint, body),
+        TestSessionCommands::Remove { name } => {
+            if !sessions.remove(&name)? {
+                bail!("Test session '{}' not found", name);
+            }
+            println!("Removed test session '{}'", name);
+            Ok(())
+        }
+    }
+}
+
+fn session<'a>(sessions: &'a TestSessions, name: &str) -> Result<&'a [SessionStep]> {
+    sessions.get(name).ok_or_else(|| anyhow!("Test session '{}' not found; see `dotlanth test-session list`", name))
+}
+
+fn run_session(ctx: &CommandContext, sessions: &TestSessions, name: &str) -> Result<()> {
+    let steps = session(sessions, name)?;
+    let auth_token = ctx.config.grpc.auth_token.clone().or_else(|| std::env::var("DOTLANTH_AUTH_TOKEN").ok());
+
+    println!("Running test session '{}' ({} steps)", name, steps.len());
+    let mut failed = 0;
+    let mut total_ms = 0;
+    for (i, step) in steps.iter().enumerate() {
+        let invocation = grpc_tester::invoke(&ctx.config, auth_token.as_deref(), &step.service, &step.method, &step.body);
+        total_ms += invocation.duration_ms;
+
+        let status = if invocation.success { "PASS" } else { "FAIL" };
+        println!(
+            "{:>3}. {} {:<50} {:>6}ms",
+            i + 1,
+            status,
+            grpc_tester::endpoint_key(&step.service, &step.method),
+            invocation.duration_ms
+        );
+        if !invocation.success {
+            failed += 1;
+            println!("       {}", invocation.response.lines().next().unwrap_or_default());
+        }
+    }
+
+    println!();
+    println!("{}/{} passed in {}ms", steps.len() - failed, steps.len(), total_ms);
+    if failed > 0 {
+        bail!("{} of {} steps failed", failed, steps.len());
+    }
+    Ok(())
+}
+
+fn list_sessions(sessions: &TestSessions) -> Result<()> {
+    let mut any = false;
+    for (name, steps) in sessions.iter() {
+        any = true;
+        println!("{:<30} {} steps", name, steps.len());
+    }
+    if !any {
+        println!("No test sessions saved. Add one with `dotlanth test-session add` or with 'p' in the TUI's gRPC Endpoints tab.");
+    }
+    Ok(())
+}
+
+fn show_session(sessions: &TestSessions, name: &str) -> Result<()> {
+    let steps = session(sessions, name)?;
+    println!("Test session '{}'", name);
+    for (i, step) in steps.iter().enumerate() {
+        println!("{:>3}. {}", i + 1, grpc_tester::endpoint_key(&step.service, &step.method));
+        println!("       {}", step.body);
+    }
+    Ok(())
+}
+
+fn add_step(ctx: &CommandContext, sessions: &mut TestSessions, name: &str, endpoint: &str, body: Option<String>) -> Result<()> {
+    let (service, method) = grpc_tester::parse_endpoint(endpoint)?;
+    let body = match body {
+        Some(body) => body,
+        None => RequestBodies::load(&ctx.config.data_dir)?.get(&service, &method).unwrap_or("{}").to_string(),
+    };
+    serde_json::from_str::<serde_json::Value>(&body).map_err(|e| anyhow!("Request body is not valid JSON: {}", e))?;
+
+    let steps = sessions.append(name, SessionStep { service, method, body })?;
+    println!("Added {} to test session '{}' ({} steps)", endpoint, name, steps);
+    Ok(())
+}
@@ -1,5 +1,8 @@
-use anyhow::Result;
+use anyhow::{Context, Result, anyhow, bail};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -8,6 +11,10 @@ pub struct DotLanthConfig {
     pub ui: UiConfig,
     pub mock_data: MockDataConfig,
     pub grpc: GrpcConfig,
+    #[serde(default)]
+    pub health: HealthConfig,
+    #[serde(default)]
+    pub gateway: GatewayConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,6 +23,13 @@ pub struct UiConfig {
     pub refresh_rate_ms: u64,
     pub show_debug_info: bool,
     pub max_log_lines: usize,
+    /// Log entries kept in memory for the Logs tab; older entries are evicted first
+    #[serde(default = "default_log_buffer_size")]
+    pub log_buffer_size: usize,
+}
+
+fn default_log_buffer_size() -> usize {
+    10_000
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,6 +48,92 @@ pub struct GrpcConfig {
     pub client_port: u16,
     pub prefer_ipv4: bool,
     pub connection_timeout_ms: u64,
+    /// Bearer token sent with runtime RPCs; redacted in all printed output
+    #[serde(default)]
+    pub auth_token: Option<String>,
+    /// Connect to the runtime over TLS; implied by any of the `tls_*` files
+    #[serde(default)]
+    pub tls: bool,
+    /// CA that signed the runtime's certificate, instead of the system roots
+    #[serde(default)]
+    pub tls_ca_cert: Option<PathBuf>,
+    /// Certificate and key presented to runtimes that require mutual TLS
+    #[serde(default)]
+    pub tls_client_cert: Option<PathBuf>,
+    #[serde(default)]
+    pub tls_client_key: Option<PathBuf>,
+    /// Name the runtime's certificate is checked against, if not `client_host`
+    #[serde(default)]
+    pub tls_server_name: Option<String>,
+    /// Connect in plaintext even when TLS is configured; for local development only
+    #[serde(default)]
+    pub insecure: bool,
+}
+
+impl GrpcConfig {
+    pub fn uses_tls(&self) -> bool {
+        !self.insecure && (self.tls || self.tls_ca_cert.is_some() || self.tls_client_cert.is_some())
+    }
+
+    /// grpcurl flags selecting plaintext or TLS with the configured certificates
+    pub fn grpcurl_transport_args(&self) -> Vec<String> {
+        if !self.uses_tls() {
+            return vec!["-plaintext".to_string()];
+        }
+
+        let mut args = Vec::new();
+        let files = [("-cacert", &self.tls_ca_cert), ("-cert", &self.tls_client_cert), ("-key", &self.tls_client_key)];
+        for (flag, path) in files {
+            if let Some(path) = path {
+                args.extend([flag.to_string(), path.display().to_string()]);
+            }
+        }
+        if let Some(name) = &self.tls_server_name {
+            args.extend(["-servername".to_string(), name.clone()]);
+        }
+        args
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthConfig {
+    /// Delay between probe rounds when watching cluster status
+    pub probe_interval_ms: u64,
+    pub probe_timeout_ms: u64,
+    /// Consecutive failed probes before a node is marked unhealthy
+    pub failure_threshold: u32,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            probe_interval_ms: 5000,
+            probe_timeout_ms: 2000,
+            failure_threshold: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GatewayConfig {
+    /// Base URL of the REST gateway, for commands that use its HTTP API
+    pub url: String,
+    /// Bearer token sent to the gateway; redacted in all printed output
+    #[serde(default)]
+    pub auth_token: Option<String>,
+    /// API key sent to the gateway when no bearer token is set
+    #[serde(default)]
+    pub api_key: Option<String>,
+}
+
+impl Default for GatewayConfig {
+    fn default() -> Self {
+        Self {
+            url: "http://127.0.0.1:8080".to_string(),
+            auth_token: None,
+            api_key: None,
+        }
+    }
 }
 
 impl Default for DotLanthConfig {
@@ -45,6 +145,7 @@ impl Default for DotLanthConfig {
                 refresh_rate_ms: 1000,
                 show_debug_info: false,
                 max_log_lines: 1000,
+                log_buffer_size: default_log_buffer_size(),
             },
             mock_data: MockDataConfig {
                 generate_sample_data: true,
@@ -59,41 +160,390 @@ impl Default for DotLanthConfig {
                 client_port: 50051,
                 prefer_ipv4: true,
                 connection_timeout_ms: 10000,
+                auth_token: None,
+                tls: false,
+                tls_ca_cert: None,
+                tls_client_cert: None,
+                tls_client_key: None,
+                tls_server_name: None,
+                insecure: false,
             },
+            health: HealthConfig::default(),
+            gateway: GatewayConfig::default(),
+        }
+    }
+}
+
+/// Prefix of the environment variables that override config keys, e.g. `DOTLANTH_GRPC_CLIENT_PORT`
+const ENV_PREFIX: &str = "DOTLANTH_";
+
+/// Shown instead of secret values
+const REDACTED: &str = "********";
+
+/// Where an effective configuration value came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigOrigin {
+    Default,
+    File { path: PathBuf, line: Option<usize> },
+    Env(String),
+    Cli(&'static str),
+}
+
+impl fmt::Display for ConfigOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigOrigin::Default => write!(f, "default"),
+            ConfigOrigin::File { path, line: Some(line) } => write!(f, "{}:{}", path.display(), line),
+            ConfigOrigin::File { path, line: None } => write!(f, "{}", path.display()),
+            ConfigOrigin::Env(var) => write!(f, "env ${}", var),
+            ConfigOrigin::Cli(flag) => write!(f, "flag {}", flag),
+        }
+    }
+}
+
+/// Config values given as command-line flags
+#[derive(Debug, Clone, Default)]
+pub struct CliOverrides {
+    pub data_dir: Option<PathBuf>,
+    pub insecure: bool,
+}
+
+/// Effective configuration together with the origin of every value
+#[derive(Debug, Clone)]
+pub struct ResolvedConfig {
+    pub config: DotLanthConfig,
+    /// Keyed by dotted path, e.g. `grpc.client_port`
+    pub origins: BTreeMap<String, ConfigOrigin>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConfigFormat {
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// YAML for `.yaml`/`.yml` files, TOML otherwise
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml") | Some("yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Toml,
+        }
+    }
+
+    /// 1-based line of `key` within `content`, tracking TOML tables and top-level YAML mappings
+    fn locate(&self, content: &str, key: &str) -> Option<usize> {
+        let (section, leaf) = key.rsplit_once('.').unwrap_or(("", key));
+        let separator = match self {
+            ConfigFormat::Toml => '=',
+            ConfigFormat::Yaml => ':',
+        };
+        let mut current = String::new();
+
+        for (index, line) in content.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let indented = line.starts_with(char::is_whitespace);
+            match self {
+                ConfigFormat::Toml if trimmed.starts_with('[') => {
+                    current = trimmed.trim_matches(|c| c == '[' || c == ']').trim().to_string();
+                    continue;
+                }
+                ConfigFormat::Yaml if !indented && trimmed.ends_with(':') => {
+                    current = trimmed.trim_end_matches(':').trim().to_string();
+                    continue;
+                }
+                ConfigFormat::Yaml if !indented => current.clear(),
+                _ => {}
+            }
+
+            let matches_key = trimmed.strip_prefix(leaf).is_some_and(|rest| rest.trim_start().starts_with(separator));
+            if current == section && matches_key {
+                return Some(index + 1);
+            }
+        }
+        None
+    }
+}
+
+/// A config file parsed into dotted keys, kept with its source for error locations
+struct ConfigFile {
+    path: PathBuf,
+    format: ConfigFormat,
+    content: String,
+    values: BTreeMap<String, Value>,
+}
+
+impl ConfigFile {
+    fn read(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).with_context(|| format!("Failed to read config file {}", path.display()))?;
+        let format = ConfigFormat::from_path(path);
+        let parsed: Value = match format {
+            ConfigFormat::Toml => toml::from_str(&content).map_err(|e| anyhow!("{}: {}", path.display(), e))?,
+            ConfigFormat::Yaml => serde_yaml::from_str(&content).map_err(|e| anyhow!("{}: {}", path.display(), e))?,
+        };
+        if !parsed.is_object() && !parsed.is_null() {
+            bail!("{}: expected a mapping of config sections", path.display());
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            format,
+            values: flatten(&parsed),
+            content,
+        })
+    }
+
+    fn origin(&self, key: &str) -> ConfigOrigin {
+        ConfigOrigin::File {
+            path: self.path.clone(),
+            line: self.format.locate(&self.content, key),
+        }
+    }
+
+    /// Rejects keys the config does not define and values of the wrong type
+    fn check_keys(&self, defaults: &BTreeMap<String, Value>, errors: &mut Vec<String>) {
+        for (key, value) in &self.values {
+            match defaults.get(key) {
+                None => errors.push(format!("{}: unknown key `{}`", self.origin(key), key)),
+                Some(default) if !same_kind(default, value) => errors.push(format!("{}: `{}` expects {}, found {}", self.origin(key), key, kind_name(default), redact(key, value))),
+                Some(_) => {}
+            }
         }
     }
 }
 
 impl DotLanthConfig {
+    /// Every effective value as `(key, rendered value, origin)`, with secrets redacted
+    pub fn effective_values<'a>(&self, origins: &'a BTreeMap<String, ConfigOrigin>) -> Vec<(String, String, &'a ConfigOrigin)> {
+        let values = flatten(&serde_json::to_value(self).unwrap_or_default());
+        values
+            .into_iter()
+            .filter_map(|(key, value)| {
+                let origin = origins.get(&key)?;
+                let rendered = redact(&key, &value);
+                Some((key, rendered, origin))
+            })
+            .collect()
+    }
+
+    /// Loads a config file strictly; missing keys take their defaults
     pub fn load_from_file(path: impl AsRef<Path>) -> Result<Self> {
-        let content = std::fs::read_to_string(path)?;
-        let config: Self = toml::from_str(&content)?;
-        Ok(config)
+        Ok(Self::validate_file(path)?.config)
+    }
+
+    /// Checks a config file against the schema and value ranges without applying env or CLI overrides
+    pub fn validate_file(path: impl AsRef<Path>) -> Result<ResolvedConfig> {
+        Self::resolve_layers(Some(ConfigFile::read(path.as_ref())?), false, CliOverrides::default())
     }
 
     pub fn save_to_file(&self, path: impl AsRef<Path>) -> Result<()> {
-        let content = toml::to_string_pretty(self)?;
+        let content = match ConfigFormat::from_path(path.as_ref()) {
+            ConfigFormat::Toml => toml::to_string_pretty(self)?,
+            ConfigFormat::Yaml => serde_yaml::to_string(self)?,
+        };
         std::fs::write(path, content)?;
         Ok(())
     }
 
-    pub fn resolve_config(cli_config: Option<PathBuf>, cli_data_dir: Option<PathBuf>) -> Result<Self> {
-        let mut config = if let Some(config_path) = cli_config {
-            Self::load_from_file(config_path)?
-        } else if let Ok(env_config) = std::env::var("DOTLANTH_CONFIG") {
-            Self::load_from_file(env_config)?
-        } else {
-            Self::default()
+    /// Resolves the effective config with precedence CLI flags > environment > config file > defaults
+    pub fn resolve_config(cli_config: Option<PathBuf>, overrides: CliOverrides) -> Result<ResolvedConfig> {
+        let file = match cli_config {
+            Some(path) => Some(ConfigFile::read(&path)?),
+            None => match std::env::var(format!("{}CONFIG", ENV_PREFIX)) {
+                Ok(path) => Some(ConfigFile::read(Path::new(&path))?),
+                Err(_) => None,
+            },
         };
 
-        // CLI data_dir overrides environment settings
-        if let Some(data_dir) = cli_data_dir {
-            config.data_dir = data_dir;
-        } else if let Ok(env_data_dir) = std::env::var("DOTLANTH_DATA_DIR") {
-            config.data_dir = PathBuf::from(env_data_dir);
+        tracing::info!(
+            "Configuration precedence: CLI flags > environment ({}*) > {} > defaults",
+            ENV_PREFIX,
+            file.as_ref().map_or_else(|| "no config file".to_string(), |f| f.path.display().to_string())
+        );
+
+        let resolved = Self::resolve_layers(file, true, overrides)?;
+        for (key, value, origin) in resolved.config.effective_values(&resolved.origins) {
+            if *origin != ConfigOrigin::Default {
+                tracing::info!("  {} = {} ({})", key, value, origin);
+            }
+        }
+
+        std::fs::create_dir_all(&resolved.config.data_dir)?;
+        Ok(resolved)
+    }
+
+    fn resolve_layers(file: Option<ConfigFile>, use_env: bool, overrides: CliOverrides) -> Result<ResolvedConfig> {
+        let defaults = flatten(&serde_json::to_value(Self::default())?);
+        let mut values = defaults.clone();
+        let mut origins: BTreeMap<String, ConfigOrigin> = defaults.keys().map(|k| (k.clone(), ConfigOrigin::Default)).collect();
+        let mut errors = Vec::new();
+
+        if let Some(file) = &file {
+            file.check_keys(&defaults, &mut errors);
+            for (key, value) in &file.values {
+                if defaults.contains_key(key) {
+                    values.insert(key.clone(), value.clone());
+                    origins.insert(key.clone(), file.origin(key));
+                }
+            }
+        }
+
+        if use_env {
+            for (key, default) in &defaults {
+                let var = env_var_name(key);
+                let Ok(raw) = std::env::var(&var) else { continue };
+                match parse_env_value(default, &raw) {
+                    Some(value) => {
+                        values.insert(key.clone(), value);
+                        origins.insert(key.clone(), ConfigOrigin::Env(var));
+                    }
+                    None => errors.push(format!("${}: expects {}, found `{}`", var, kind_name(default), redact(key, &Value::String(raw)))),
+                }
+            }
+        }
+
+        if let Some(data_dir) = overrides.data_dir {
+            values.insert("data_dir".to_string(), Value::String(data_dir.display().to_string()));
+            origins.insert("data_dir".to_string(), ConfigOrigin::Cli("--data-dir"));
         }
+        if overrides.insecure {
+            values.insert("grpc.insecure".to_string(), Value::Bool(true));
+            origins.insert("grpc.insecure".to_string(), ConfigOrigin::Cli("--insecure"));
+        }
+
+        if !errors.is_empty() {
+            bail!("Invalid configuration:\n  {}", errors.join("\n  "));
+        }
+
+        let config: Self = serde_json::from_value(unflatten(values)).context("Invalid configuration")?;
+        let range_errors: Vec<String> = config
+            .validate()
+            .into_iter()
+            .map(|(key, message)| format!("{}: `{}` {}", origins.get(key).unwrap_or(&ConfigOrigin::Default), key, message))
+            .collect();
+        if !range_errors.is_empty() {
+            bail!("Invalid configuration:\n  {}", range_errors.join("\n  "));
+        }
+
+        Ok(ResolvedConfig { config, origins })
+    }
 
-        std::fs::create_dir_all(&config.data_dir)?;
-        Ok(config)
+    /// Range checks, returned as `(key, problem)` pairs
+    fn validate(&self) -> Vec<(&'static str, String)> {
+        let mut errors = Vec::new();
+        let mut check = |ok: bool, key: &'static str, message: &str| {
+            if !ok {
+                errors.push((key, message.to_string()));
+            }
+        };
+
+        check(["default", "dark", "light"].contains(&self.ui.theme.as_str()), "ui.theme", "must be one of: default, dark, light");
+        check((100..=10000).contains(&self.ui.refresh_rate_ms), "ui.refresh_rate_ms", "must be between 100 and 10000");
+        check(self.ui.max_log_lines > 0, "ui.max_log_lines", "must be greater than 0");
+        check(self.ui.log_buffer_size > 0, "ui.log_buffer_size", "must be greater than 0");
+        check(self.grpc.server_port > 0, "grpc.server_port", "must be greater than 0");
+        check(self.grpc.client_port > 0, "grpc.client_port", "must be greater than 0");
+        check(self.grpc.connection_timeout_ms > 0, "grpc.connection_timeout_ms", "must be greater than 0");
+        check(self.health.probe_interval_ms >= 100, "health.probe_interval_ms", "must be at least 100");
+        check(self.health.probe_timeout_ms >= 100, "health.probe_timeout_ms", "must be at least 100");
+        check(self.health.failure_threshold > 0, "health.failure_threshold", "must be greater than 0");
+        check(
+            self.gateway.url.starts_with("http://") || self.gateway.url.starts_with("https://"),
+            "gateway.url",
+            "must start with http:// or https://",
+        );
+
+        errors
+    }
+}
+
+/// Whether `key` holds a secret that must never be printed
+pub fn is_secret_key(key: &str) -> bool {
+    let leaf = key.rsplit('.').next().unwrap_or(key);
+    ["token", "key", "secret", "password"].iter().any(|marker| leaf.contains(marker))
+}
+
+fn redact(key: &str, value: &Value) -> String {
+    match value {
+        Value::Null => "(not set)".to_string(),
+        _ if is_secret_key(key) => REDACTED.to_string(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn env_var_name(key: &str) -> String {
+    format!("{}{}", ENV_PREFIX, key.replace('.', "_").to_uppercase())
+}
+
+/// Parses an environment override using the type of the key's default value
+fn parse_env_value(default: &Value, raw: &str) -> Option<Value> {
+    match default {
+        Value::Bool(_) => raw.parse::<bool>().ok().map(Value::Bool),
+        Value::Number(n) if n.is_u64() => raw.parse::<u64>().ok().map(Value::from),
+        Value::Number(_) => raw.parse::<f64>().ok().map(Value::from),
+        _ => Some(Value::String(raw.to_string())),
+    }
+}
+
+fn same_kind(default: &Value, value: &Value) -> bool {
+    match (default, value) {
+        (Value::Null, Value::Null | Value::String(_)) => true,
+        (Value::Bool(_), Value::Bool(_)) | (Value::String(_), Value::String(_)) => true,
+        (Value::Number(d), Value::Number(v)) => !d.is_u64() || v.is_u64(),
+        _ => false,
+    }
+}
+
+fn kind_name(default: &Value) -> &'static str {
+    match default {
+        Value::Bool(_) => "a boolean",
+        Value::Number(n) if n.is_u64() => "a non-negative integer",
+        Value::Number(_) => "a number",
+        _ => "a string",
+    }
+}
+
+/// Flattens nested objects into dotted keys; every non-object value is a leaf
+fn flatten(value: &Value) -> BTreeMap<String, Value> {
+    fn walk(prefix: &str, value: &Value, out: &mut BTreeMap<String, Value>) {
+        match value {
+            Value::Object(map) => {
+                for (key, child) in map {
+                    let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                    walk(&path, child, out);
+                }
+            }
+            leaf => {
+                out.insert(prefix.to_string(), leaf.clone());
+            }
+        }
+    }
+
+    let mut out = BTreeMap::new();
+    if !value.is_null() {
+        walk("", value, &mut out);
+    }
+    out
+}
+
+fn unflatten(values: BTreeMap<String, Value>) -> Value {
+    let mut root = Map::new();
+    for (key, value) in values {
+        let mut parts: Vec<&str> = key.split('.').collect();
+        let leaf = parts.pop().unwrap_or_default();
+        let mut node = &mut root;
+        for part in parts {
+            node = node
+                .entry(part.to_string())
+                .or_insert_with(|| Value::Object(Map::new()))
+                .as_object_mut()
+                .expect("config sections are objects");
+        }
+        node.insert(leaf.to_string(), value);
     }
+    Value::Object(root)
 }
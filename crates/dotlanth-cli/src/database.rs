@@ -1,3 +1,5 @@
+use crate::deployment::DeploymentProgress;
+use crate::health::NodeHealth;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -13,6 +15,11 @@ pub struct NodeInfo {
     pub version: String,
     pub capabilities: Vec<String>,
     pub metadata: Value,
+    #[serde(default)]
+    pub health: NodeHealth,
+    /// Draining nodes keep their deployments but are no longer deployment targets
+    #[serde(default)]
+    pub draining: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,6 +40,8 @@ pub struct DeploymentInfo {
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
     pub config: Value,
+    #[serde(default)]
+    pub progress: Option<DeploymentProgress>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -69,15 +78,17 @@ pub struct DotLanthDatabase {
     deployments: Arc<Mutex<HashMap<String, DeploymentInfo>>>,
     metrics: Arc<Mutex<Vec<MetricEntry>>>,
     logs: Arc<Mutex<Vec<LogEntry>>>,
+    log_capacity: usize,
 }
 
 impl DotLanthDatabase {
-    pub fn new(_storage_path: impl AsRef<std::path::Path>) -> Result<Self> {
+    pub fn new(_storage_path: impl AsRef<std::path::Path>, log_capacity: usize) -> Result<Self> {
         let db = Self {
             nodes: Arc::new(Mutex::new(HashMap::new())),
             deployments: Arc::new(Mutex::new(HashMap::new())),
             metrics: Arc::new(Mutex::new(Vec::new())),
             logs: Arc::new(Mutex::new(Vec::new())),
+            log_capacity: log_capacity.max(1),
         };
         println!("Mock Database initialized (placeholder for dotdb integration)");
         Ok(db)
@@ -99,6 +110,24 @@ impl DotLanthDatabase {
         Ok(nodes.values().cloned().collect())
     }
 
+    pub fn update_node_health(&self, node_id: &str, health: NodeHealth, status: NodeStatus) -> Result<()> {
+        let mut nodes = self.nodes.lock().unwrap();
+        if let Some(node) = nodes.get_mut(node_id) {
+            if health.last_success > node.health.last_success {
+                node.last_heartbeat = chrono::Utc::now();
+            }
+            node.health = health;
+            node.status = status;
+        }
+        Ok(())
+    }
+
+    /// Returns whether the node exists
+    pub fn set_node_draining(&self, node_id: &str, draining: bool) -> Result<bool> {
+        let mut nodes = self.nodes.lock().unwrap();
+        Ok(nodes.get_mut(node_id).map(|node| node.draining = draining).is_some())
+    }
+
     pub fn remove_node(&self, node_id: &str) -> Result<()> {
         let mut nodes = self.nodes.lock().unwrap();
         nodes.remove(node_id);
@@ -130,6 +159,21 @@ impl DotLanthDatabase {
         Ok(())
     }
 
+    pub fn update_deployment_progress(&self, deployment_id: &str, progress: DeploymentProgress) -> Result<()> {
+        let mut deployments = self.deployments.lock().unwrap();
+        if let Some(deployment) = deployments.get_mut(deployment_id) {
+            deployment.progress = Some(progress);
+            deployment.updated_at = chrono::Utc::now();
+        }
+        Ok(())
+    }
+
+    pub fn delete_deployment(&self, deployment_id: &str) -> Result<()> {
+        let mut deployments = self.deployments.lock().unwrap();
+        deployments.remove(deployment_id);
+        Ok(())
+    }
+
     pub fn store_metrics(&self, metric: MetricEntry) -> Result<()> {
         let mut metrics = self.metrics.lock().unwrap();
         metrics.push(metric);
@@ -153,7 +197,7 @@ impl DotLanthDatabase {
     pub fn store_log(&self, log: LogEntry) -> Result<()> {
         let mut logs = self.logs.lock().unwrap();
         logs.push(log);
-        let excess = logs.len().saturating_sub(1000);
+        let excess = logs.len().saturating_sub(self.log_capacity);
         if excess > 0 {
             logs.drain(0..excess);
         }
@@ -170,6 +214,14 @@ impl DotLanthDatabase {
         Ok(filtered.into_iter().rev().take(limit).collect())
     }
 
+    /// Logs stored after the entry with `after_id`, oldest first. Returns everything
+    /// when `after_id` is `None` or has already been evicted.
+    pub fn get_logs_after(&self, after_id: Option<&str>) -> Result<Vec<LogEntry>> {
+        let logs = self.logs.lock().unwrap();
+        let start = after_id.and_then(|id| logs.iter().rposition(|l| l.id == id)).map_or(0, |pos| pos + 1);
+        Ok(logs[start..].to_vec())
+    }
+
     pub fn generate_sample_data(&self) -> Result<()> {
         self.generate_sample_nodes()?;
         self.generate_sample_deployments()?;
@@ -0,0 +1,532 @@
+use crate::commands::stream_vm_service_at;
+use crate::config::{GrpcConfig, HealthConfig};
+use crate::database::{DeploymentInfo, DeploymentStatus, DotLanthDatabase, NodeInfo, NodeStatus};
+use crate::health::{self, HealthProbe};
+use dotvm_core::bytecode::VmArchitecture;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::mpsc::Sender;
+use std::time::Instant;
+
+const DEPLOY_WITH_PROGRESS_METHOD: &str = "vm_service.VmService/DeployDotWithProgress";
+
+/// Stages a dot goes through while being deployed, in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeploymentStage {
+    Upload,
+    Validation,
+    Transpilation,
+    Registration,
+    Activation,
+}
+
+impl DeploymentStage {
+    pub const ALL: [DeploymentStage; 5] = [
+        DeploymentStage::Upload,
+        DeploymentStage::Validation,
+        DeploymentStage::Transpilation,
+        DeploymentStage::Registration,
+        DeploymentStage::Activation,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeploymentStage::Upload => "upload",
+            DeploymentStage::Validation => "validation",
+            DeploymentStage::Transpilation => "transpilation",
+            DeploymentStage::Registration => "registration",
+            DeploymentStage::Activation => "activation",
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        Self::ALL.iter().position(|s| s == self).unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StageState {
+    Pending,
+    Running,
+    Completed,
+    Failed(String),
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StageProgress {
+    pub stage: DeploymentStage,
+    pub state: StageState,
+    pub duration_ms: Option<u64>,
+    pub message: Option<String>,
+}
+
+/// A single status message from the deployment stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeploymentUpdate {
+    pub stage: DeploymentStage,
+    pub state: StageState,
+    pub duration_ms: Option<u64>,
+    pub message: Option<String>,
+    /// Generated ABI, sent once validation completes
+    pub abi: Option<Value>,
+}
+
+/// Per-stage progress of a deployment, shared by `dotlanth deploy` and the TUI Deployments tab
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeploymentProgress {
+    pub stages: Vec<StageProgress>,
+}
+
+impl Default for DeploymentProgress {
+    fn default() -> Self {
+        Self {
+            stages: DeploymentStage::ALL
+                .iter()
+                .map(|&stage| StageProgress {
+                    stage,
+                    state: StageState::Pending,
+                    duration_ms: None,
+                    message: None,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl DeploymentProgress {
+    pub fn apply(&mut self, update: &DeploymentUpdate) {
+        let entry = &mut self.stages[update.stage.index()];
+        entry.state = update.state.clone();
+        if update.duration_ms.is_some() {
+            entry.duration_ms = update.duration_ms;
+        }
+        if update.message.is_some() {
+            entry.message = update.message.clone();
+        }
+    }
+
+    /// The stage currently running, or the last one that finished
+    pub fn current_stage(&self) -> Option<&StageProgress> {
+        self.stages
+            .iter()
+            .find(|s| matches!(s.state, StageState::Running | StageState::Failed(_)))
+            .or_else(|| self.stages.iter().rev().find(|s| s.state == StageState::Completed))
+    }
+
+    pub fn failed_stage(&self) -> Option<&StageProgress> {
+        self.stages.iter().find(|s| matches!(s.state, StageState::Failed(_)))
+    }
+
+    pub fn total_duration_ms(&self) -> u64 {
+        self.stages.iter().filter_map(|s| s.duration_ms).sum()
+    }
+
+    pub fn summary(&self) -> String {
+        match self.current_stage() {
+            Some(stage) => match &stage.state {
+                StageState::Running => format!("{} ({}/{})", stage.stage.name(), stage.stage.index() + 1, DeploymentStage::ALL.len()),
+                StageState::Failed(_) => format!("{} failed", stage.stage.name()),
+                _ if stage.stage == DeploymentStage::Activation => "done".to_string(),
+                _ => format!("{} done", stage.stage.name()),
+            },
+            None => "pending".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DeploymentRequest {
+    pub deployment_id: String,
+    pub dot_name: String,
+    pub dot_file: std::path::PathBuf,
+    /// Architecture the dot is transpiled for
+    pub architecture: VmArchitecture,
+    pub dry_run: bool,
+    pub rollback_on_failure: bool,
+    /// Probe targets before using them and move on to the next node when one is unhealthy
+    pub failover: bool,
+    pub health: HealthConfig,
+    /// Transport and credentials for the target node's runtime
+    pub grpc: GrpcConfig,
+}
+
+/// Runs the deployment stages and reports each transition on `updates`.
+///
+/// Upload and the local checks run here; from there the runtime of the target node deploys the
+/// dot and streams its own stages, which are reported as they arrive. Consumers only see the
+/// `DeploymentUpdate` messages.
+pub fn run_deployment(database: &DotLanthDatabase, request: &DeploymentRequest, updates: Sender<DeploymentUpdate>) {
+    let mut pipeline = Pipeline {
+        database,
+        request,
+        updates,
+        progress: DeploymentProgress::default(),
+        current: None,
+        source: String::new(),
+        abi: None,
+        target: None,
+        registered: false,
+        failed: false,
+        tried: Vec::new(),
+        failures: Vec::new(),
+    };
+    if let Err(e) = pipeline.run() {
+        pipeline.fail(&e.to_string());
+    }
+}
+
+struct Pipeline<'a> {
+    database: &'a DotLanthDatabase,
+    request: &'a DeploymentRequest,
+    updates: Sender<DeploymentUpdate>,
+    progress: DeploymentProgress,
+    /// The stage running and when it started
+    current: Option<(DeploymentStage, Instant)>,
+    source: String,
+    /// ABI generated by the local checks, reported once validation completes
+    abi: Option<Value>,
+    /// Node the dot is being deployed to
+    target: Option<NodeInfo>,
+    registered: bool,
+    failed: bool,
+    /// Nodes already chosen as target, in order
+    tried: Vec<String>,
+    /// Why each failed-over node was abandoned
+    failures: Vec<String>,
+}
+
+impl Pipeline<'_> {
+    fn run(&mut self) -> anyhow::Result<()> {
+        self.start(DeploymentStage::Upload);
+        let message = self.upload()?;
+        self.complete(Some(message));
+
+        self.start(DeploymentStage::Validation);
+        self.validate()?;
+        if self.request.dry_run {
+            self.complete(None);
+            for stage in &DeploymentStage::ALL[DeploymentStage::Transpilation.index()..] {
+                self.send(*stage, StageState::Skipped, None, Some("dry run".to_string()), None);
+            }
+            return Ok(());
+        }
+
+        self.deploy()
+    }
+
+    fn send(&mut self, stage: DeploymentStage, state: StageState, duration_ms: Option<u64>, message: Option<String>, abi: Option<Value>) {
+        let update = DeploymentUpdate {
+            stage,
+            state,
+            duration_ms,
+            message,
+            abi,
+        };
+        self.progress.apply(&update);
+        if self.registered {
+            let _ = self.database.update_deployment_progress(&self.request.deployment_id, self.progress.clone());
+        }
+        // A dropped receiver only means nobody is watching; the deployment still finishes
+        let _ = self.updates.send(update);
+    }
+
+    fn start(&mut self, stage: DeploymentStage) {
+        self.send(stage, StageState::Running, None, None, None);
+        self.current = Some((stage, Instant::now()));
+    }
+
+    fn complete(&mut self, message: Option<String>) {
+        if let Some((stage, started)) = self.current.take() {
+            let abi = if stage == DeploymentStage::Validation { self.abi.take() } else { None };
+            self.send(stage, StageState::Completed, Some(started.elapsed().as_millis() as u64), message, abi);
+        }
+    }
+
+    /// Fails the running stage and skips the rest, cleaning up the deployment record if one was made
+    fn fail(&mut self, reason: &str) {
+        if self.failed {
+            return;
+        }
+        self.failed = true;
+        let (stage, started) = self.current.take().unwrap_or((DeploymentStage::Upload, Instant::now()));
+        let message = self.registered.then(|| self.handle_failure(reason));
+        self.send(stage, StageState::Failed(reason.to_string()), Some(started.elapsed().as_millis() as u64), message, None);
+        for rest in DeploymentStage::ALL.iter().skip(stage.index() + 1) {
+            self.send(*rest, StageState::Skipped, None, None, None);
+        }
+    }
+
+    /// Reads the dot and, unless this is a dry run, picks the node it is deployed to
+    fn upload(&mut self) -> anyhow::Result<String> {
+        let bytes = std::fs::read(&self.request.dot_file)?;
+        let size = bytes.len();
+        self.source = String::from_utf8(bytes).map_err(|_| anyhow::anyhow!("dot file is not valid UTF-8"))?;
+        if self.request.dry_run {
+            return Ok(format!("{} bytes", size));
+        }
+
+        let node = self.select_target()?;
+        let message = format!("{} bytes for node {} ({}){}", size, node.id, node.address, self.failover_note());
+        self.target = Some(node);
+        Ok(message)
+    }
+
+    /// Checks that can be made without a runtime; the runtime validates the dot again when it deploys it
+    fn validate(&mut self) -> anyhow::Result<()> {
+        if self.source.trim().is_empty() {
+            return Err(anyhow::anyhow!("dot file is empty"));
+        }
+
+        let mut depth: i64 = 0;
+        for c in self.source.chars() {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => {}
+            }
+            if depth < 0 {
+                return Err(anyhow::anyhow!("unbalanced '}}' in dot source"));
+            }
+        }
+        if depth != 0 {
+            return Err(anyhow::anyhow!("unbalanced '{{' in dot source"));
+        }
+
+        self.abi = Some(generate_abi(&self.source, &self.request.dot_name));
+        Ok(())
+    }
+
+    /// Deploys the dot on the target node's runtime, reporting the stages it streams back. With
+    /// failover, a runtime that cannot be reached is abandoned for the next healthy node.
+    fn deploy(&mut self) -> anyhow::Result<()> {
+        let request = serde_json::json!({
+            "dot_name": self.request.dot_name,
+            "dot_source": self.source,
+            "metadata": { "dependencies": parse_dependencies(&self.source) },
+            "options": {
+                "validate_abi": true,
+                "target_architecture": self.request.architecture.to_string(),
+            },
+        });
+
+        let grpc = &self.request.grpc;
+        loop {
+            let node = self.target.clone().ok_or_else(|| anyhow::anyhow!("no target node selected"))?;
+            let address = node.address.trim_start_matches("http://").trim_start_matches("https://");
+            let mut received = false;
+            let result = stream_vm_service_at(grpc, address, DEPLOY_WITH_PROGRESS_METHOD, &request, |message| {
+                received = true;
+                self.on_progress(&message)
+            });
+
+            match result {
+                // The runtime already reported the stage that failed
+                Err(_) if self.failed => return Ok(()),
+                Err(e) if !received && self.request.failover => {
+                    self.failures.push(format!("node {} ({}) unreachable: {}", node.id, node.address, e));
+                    self.target = Some(self.select_target()?);
+                }
+                Err(e) => return Err(e),
+                Ok(()) if !self.progress.stages.iter().all(|s| matches!(s.state, StageState::Completed | StageState::Skipped)) => {
+                    return Err(anyhow::anyhow!("runtime on node {} ended the deployment before it finished", node.id));
+                }
+                Ok(()) => return Ok(()),
+            }
+        }
+    }
+
+    /// Applies one `DeployDotProgress` message from the runtime
+    fn on_progress(&mut self, progress: &Value) -> anyhow::Result<()> {
+        let stage = match progress["stage"].as_str().unwrap_or_default() {
+            "validation" => DeploymentStage::Validation,
+            "transpilation" => DeploymentStage::Transpilation,
+            "registration" => DeploymentStage::Registration,
+            "activation" => DeploymentStage::Activation,
+            other => return Err(anyhow::anyhow!("runtime reported unknown deployment stage '{}'", other)),
+        };
+        let message = progress["message"].as_str().filter(|m| !m.is_empty()).map(str::to_string);
+        // Validation already started with the local checks
+        if self.current.is_none_or(|(current, _)| current != stage) {
+            self.start(stage);
+        }
+
+        match progress["state"].as_str().unwrap_or_default() {
+            "DEPLOY_STAGE_STATE_RUNNING" => {}
+            "DEPLOY_STAGE_STATE_COMPLETED" => {
+                let message = match stage {
+                    DeploymentStage::Transpilation => Some(format!("target {}", self.request.architecture)),
+                    DeploymentStage::Registration => Some(self.register()?),
+                    DeploymentStage::Activation => {
+                        self.database.update_deployment_status(&self.request.deployment_id, DeploymentStatus::Running)?;
+                        deployed_version(&progress["result"])
+                    }
+                    _ => message,
+                };
+                self.complete(message);
+            }
+            "DEPLOY_STAGE_STATE_SKIPPED" => {
+                self.current = None;
+                self.send(stage, StageState::Skipped, None, message, None);
+            }
+            "DEPLOY_STAGE_STATE_FAILED" => self.fail(message.as_deref().unwrap_or("failed on the runtime")),
+            other => return Err(anyhow::anyhow!("runtime reported unknown stage state '{}'", other)),
+        }
+        Ok(())
+    }
+
+    /// Picks the node to deploy to. Draining and maintenance nodes are never targeted. With
+    /// failover, each candidate is probed first and unhealthy ones are recorded and skipped.
+    fn select_target(&mut self) -> anyhow::Result<NodeInfo> {
+        let mut candidates: Vec<NodeInfo> = self
+            .database
+            .list_nodes()?
+            .into_iter()
+            .filter(|n| !n.draining && !matches!(n.status, NodeStatus::Maintenance) && !self.tried.contains(&n.id))
+            .collect();
+        // Nodes believed online are tried first
+        candidates.sort_by(|a, b| a.id.cmp(&b.id));
+        candidates.sort_by_key(|n| !matches!(n.status, NodeStatus::Online));
+
+        if !self.request.failover {
+            let node = candidates
+                .into_iter()
+                .find(|n| matches!(n.status, NodeStatus::Online))
+                .ok_or_else(|| anyhow::anyhow!("No online nodes available for deployment"))?;
+            self.tried.push(node.id.clone());
+            return Ok(node);
+        }
+
+        let probe = HealthProbe::from_config(&self.request.health);
+        for node in candidates {
+            self.tried.push(node.id.clone());
+            match health::probe_node(self.database, &node, &probe, self.request.health.failure_threshold)?.0 {
+                Ok(_) => return Ok(node),
+                Err(e) => self.failures.push(format!("node {} ({}) unhealthy: {}", node.id, node.address, e)),
+            }
+        }
+        Err(anyhow::anyhow!("No healthy nodes available for deployment{}", self.failover_note()))
+    }
+
+    fn failover_note(&self) -> String {
+        if self.failures.is_empty() { String::new() } else { format!("; failed over from: {}", self.failures.join("; ")) }
+    }
+
+    /// Records the deployment once the runtime has registered the dot
+    fn register(&mut self) -> anyhow::Result<String> {
+        let node = self.target.clone().ok_or_else(|| anyhow::anyhow!("no target node selected"))?;
+        let deployment = DeploymentInfo {
+            id: self.request.deployment_id.clone(),
+            dot_name: self.request.dot_name.clone(),
+            dot_version: "1.0.0".to_string(),
+            node_id: node.id.clone(),
+            status: DeploymentStatus::Pending,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+            config: serde_json::json!({
+                "file_path": self.request.dot_file.to_string_lossy(),
+                "architecture": self.request.architecture.to_string(),
+                "memory": "512MB",
+                "cpu": "0.5"
+            }),
+            progress: Some(self.progress.clone()),
+        };
+        self.database.create_deployment(deployment)?;
+        self.registered = true;
+
+        Ok(format!("node {} ({}){}", node.id, node.address, self.failover_note()))
+    }
+
+    /// Removes or marks the recorded deployment after a failure past registration, returning what
+    /// was done. A failed redeploy is rolled back to the previous version by the runtime itself.
+    fn handle_failure(&mut self, reason: &str) -> String {
+        if self.request.rollback_on_failure {
+            match self.database.delete_deployment(&self.request.deployment_id) {
+                Ok(()) => {
+                    self.registered = false;
+                    format!("rolled back deployment {}", self.request.deployment_id)
+                }
+                Err(e) => format!("rollback of {} failed: {}", self.request.deployment_id, e),
+            }
+        } else {
+            let _ = self.database.update_deployment_status(&self.request.deployment_id, DeploymentStatus::Failed(reason.to_string()));
+            format!("deployment {} left in failed state", self.request.deployment_id)
+        }
+    }
+}
+
+/// Which version of which dot a successful deployment produced, from the runtime's `DeployDotResponse`
+fn deployed_version(result: &Value) -> Option<String> {
+    let dot_id = result["dotId"].as_str().filter(|id| !id.is_empty())?;
+    Some(format!("version {} of dot {}", result["version"], dot_id))
+}
+
+/// Another dot a dot calls, declared in its source as `requires <name> [<version constraint>]`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DotDependency {
+    pub dot_name: String,
+    /// Empty when any version will do
+    pub version_constraint: String,
+}
+
+impl std::fmt::Display for DotDependency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.version_constraint.is_empty() {
+            f.write_str(&self.dot_name)
+        } else {
+            write!(f, "{} {}", self.dot_name, self.version_constraint)
+        }
+    }
+}
+
+/// The `requires` lines of a dot source, e.g. `requires tax ">=1.2, <2";`
+pub fn parse_dependencies(source: &str) -> Vec<DotDependency> {
+    source
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("requires "))
+        .filter_map(|rest| {
+            let rest = rest.trim().trim_end_matches(';');
+            let (name, constraint) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            Some(DotDependency {
+                dot_name: Some(name.trim()).filter(|n| !n.is_empty())?.to_string(),
+                version_constraint: constraint.trim().trim_matches('"').to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Builds a minimal ABI from the dot source: the dot name, the dots it requires and the functions it declares
+pub fn generate_abi(source: &str, fallback_name: &str) -> Value {
+    let name = source
+        .split_once("dot ")
+        .and_then(|(_, rest)| rest.split('{').next())
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| fallback_name.to_string());
+
+    let functions: Vec<Value> = source
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("fn "))
+        .filter_map(|rest| {
+            let (fn_name, rest) = rest.split_once('(')?;
+            let (params, _) = rest.split_once(')')?;
+            let inputs: Vec<Value> = params
+                .split(',')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(|p| match p.split_once(':') {
+                    Some((n, t)) => serde_json::json!({ "name": n.trim(), "type": t.trim() }),
+                    None => serde_json::json!({ "name": p, "type": "any" }),
+                })
+                .collect();
+            Some(serde_json::json!({ "name": fn_name.trim(), "inputs": inputs }))
+        })
+        .collect();
+
+    serde_json::json!({
+        "dot_name": name,
+        "version": "1.0.0",
+        "dependencies": parse_dependencies(source),
+        "functions": functions,
+    })
+}
@@ -0,0 +1,383 @@
+//! Prometheus exporter for runtime metrics
+//!
+//! A poller calls `GetVMMetrics` on every target runtime on a fixed interval and
+//! folds the response into a [`MetricsRegistry`]; an HTTP listener renders the
+//! registry at `/metrics` in the Prometheus text format. Scrapes never wait on
+//! the runtime, so a scrape succeeds even while a runtime is unreachable: its last
+//! series keep being served, marked by `dotlanth_exporter_up 0` and a growing
+//! `dotlanth_exporter_data_age_seconds`, until they are older than the staleness
+//! limit.
+//!
+//! Metric names are stable:
+//!
+//! | Metric | Type | Labels | Meaning |
+//! |---|---|---|---|
+//! | `dotlanth_<name>` | gauge | runtime labels, `node` | Runtime metric `<name>` whose type is not `counter`, e.g. `dotlanth_cpu_usage_percent`, `dotlanth_dot_memory_live_bytes{dot_id}` |
+//! | `dotlanth_<name>_total` | counter | runtime labels, `node` | Runtime metric `<name>` of type `counter` |
+//! | `dotlanth_exporter_up` | gauge | `node` | 1 if the last poll of the runtime succeeded |
+//! | `dotlanth_exporter_data_age_seconds` | gauge | `node` | Time since the last successful poll |
+//! | `dotlanth_exporter_scrape_duration_seconds` | gauge | `node` | Duration of the last poll of the runtime |
+//! | `dotlanth_exporter_upstream_errors_total` | counter | `node` | Failed polls |
+//! | `dotlanth_exporter_scrapes_total` | counter | | Requests served at `/metrics` |
+//!
+//! Runtime metric and label names are reduced to `[a-zA-Z0-9_]`. The `site` and
+//! `age_secs` labels of leak suspects are dropped, as they would start a new series
+//! on every poll. Exported counters only ever grow: the exporter adds up the
+//! increase between polls and treats a smaller upstream value, as seen after the
+//! runtime restarts, as a count from zero.
+
+use crate::commands::call_vm_service_at;
+use crate::config::GrpcConfig;
+use anyhow::{Context, Result};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const GET_METRICS_METHOD: &str = "vm_service.VmService/GetVMMetrics";
+
+/// Prefix of every exported metric
+const NAMESPACE: &str = "dotlanth";
+
+/// Runtime labels that change on every poll
+const DROPPED_LABELS: [&str; 2] = ["site", "age_secs"];
+
+/// Content type of the Prometheus text exposition format
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Time a scraper gets to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct ExporterOptions {
+    /// Address the HTTP listener binds to
+    pub listen: String,
+    /// Delay between polls of the runtimes
+    pub interval: Duration,
+    /// Series of a runtime that has not answered for longer are no longer served
+    pub max_stale: Duration,
+}
+
+/// A runtime to poll and the `node` label of its series
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub node: String,
+    pub address: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MetricKind {
+    Counter,
+    Gauge,
+}
+
+impl MetricKind {
+    fn name(self) -> &'static str {
+        match self {
+            MetricKind::Counter => "counter",
+            MetricKind::Gauge => "gauge",
+        }
+    }
+}
+
+/// Sorted label pairs of a series
+type Labels = Vec<(String, String)>;
+
+/// Exported metric name and its labels
+type SeriesKey = (String, Labels);
+
+#[derive(Debug, Clone)]
+struct Family {
+    kind: MetricKind,
+    help: String,
+}
+
+/// What the exporter knows about one runtime
+#[derive(Debug, Default)]
+struct NodeState {
+    series: BTreeMap<SeriesKey, f64>,
+    up: bool,
+    last_success: Option<Instant>,
+    poll_duration: Duration,
+    errors: u64,
+}
+
+/// Upstream value a counter was last seen at and the total exported for it
+#[derive(Debug, Clone, Copy)]
+struct CounterTrack {
+    upstream: f64,
+    total: f64,
+}
+
+/// Latest series of every runtime, rendered on each scrape
+#[derive(Debug)]
+pub struct MetricsRegistry {
+    max_stale: Duration,
+    nodes: BTreeMap<String, NodeState>,
+    families: BTreeMap<String, Family>,
+    counters: HashMap<SeriesKey, CounterTrack>,
+    scrapes: u64,
+}
+
+impl MetricsRegistry {
+    pub fn new(max_stale: Duration) -> Self {
+        Self {
+            max_stale,
+            nodes: BTreeMap::new(),
+            families: BTreeMap::new(),
+            counters: HashMap::new(),
+            scrapes: 0,
+        }
+    }
+
+    /// Replaces a runtime's series with the `metrics` of a `GetVMMetrics` response
+    pub fn record_success(&mut self, node: &str, metrics: &[Value], took: Duration) {
+        let mut series = BTreeMap::new();
+        for metric in metrics {
+            let Some(upstream_name) = metric["name"].as_str() else {
+                continue;
+            };
+            let Some(value) = latest_value(metric) else {
+                continue;
+            };
+
+            let kind = if metric["type"].as_str() == Some("counter") { MetricKind::Counter } else { MetricKind::Gauge };
+            let mut name = format!("{}_{}", NAMESPACE, sanitize(upstream_name));
+            if kind == MetricKind::Counter && !name.ends_with("_total") {
+                name.push_str("_total");
+            }
+            self.families.entry(name.clone()).or_insert_with(|| Family {
+                kind,
+                help: format!("Runtime metric {}", upstream_name),
+            });
+
+            let mut labels: Labels = metric["labels"]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .filter(|(key, _)| !DROPPED_LABELS.contains(&key.as_str()) && key.as_str() != "node")
+                .map(|(key, value)| (sanitize(key), value.as_str().map_or_else(|| value.to_string(), str::to_string)))
+                .collect();
+            labels.push(("node".to_string(), node.to_string()));
+            labels.sort();
+
+            let key = (name, labels);
+            let value = match kind {
+                MetricKind::Counter => self.advance_counter(&key, value),
+                MetricKind::Gauge => value,
+            };
+            series.insert(key, value);
+        }
+
+        let state = self.nodes.entry(node.to_string()).or_default();
+        state.series = series;
+        state.up = true;
+        state.last_success = Some(Instant::now());
+        state.poll_duration = took;
+    }
+
+    /// Keeps a runtime's last series and marks them as stale
+    pub fn record_failure(&mut self, node: &str, took: Duration) {
+        let state = self.nodes.entry(node.to_string()).or_default();
+        state.up = false;
+        state.poll_duration = took;
+        state.errors += 1;
+    }
+
+    /// Adds the increase since the last poll to a counter's exported total
+    fn advance_counter(&mut self, key: &SeriesKey, upstream: f64) -> f64 {
+        let track = self.counters.entry(key.clone()).or_insert(CounterTrack { upstream: 0.0, total: 0.0 });
+        // A smaller value means the runtime restarted and counts from zero again
+        let increase = if upstream >= track.upstream { upstream - track.upstream } else { upstream };
+        track.upstream = upstream;
+        track.total += increase;
+        track.total
+    }
+
+    /// Renders every series in the Prometheus text format, counting the scrape
+    pub fn render(&mut self) -> String {
+        self.scrapes += 1;
+        let now = Instant::now();
+
+        // Group the series of all runtimes by metric, dropping runtimes silent for too long
+        let mut grouped: BTreeMap<&str, Vec<(&Labels, f64)>> = BTreeMap::new();
+        for state in self.nodes.values() {
+            if state.last_success.is_none_or(|at| now.duration_since(at) > self.max_stale) {
+                continue;
+            }
+            for ((name, labels), value) in &state.series {
+                grouped.entry(name).or_default().push((labels, *value));
+            }
+        }
+
+        let mut out = String::new();
+        for (name, series) in grouped {
+            let family = &self.families[name];
+            write_family(&mut out, name, family.kind, &family.help);
+            for (labels, value) in series {
+                write_sample(&mut out, name, labels, value);
+            }
+        }
+
+        let exporter = |metric: &str| format!("{}_exporter_{}", NAMESPACE, metric);
+        let per_node = |out: &mut String, metric: &str, kind: MetricKind, help: &str, value: &dyn Fn(&NodeState) -> f64| {
+            let name = exporter(metric);
+            write_family(out, &name, kind, help);
+            for (node, state) in &self.nodes {
+                write_sample(out, &name, &[("node".to_string(), node.clone())], value(state));
+            }
+        };
+        per_node(&mut out, "up", MetricKind::Gauge, "1 if the last poll of the runtime succeeded", &|state| f64::from(u8::from(state.up)));
+        per_node(&mut out, "data_age_seconds", MetricKind::Gauge, "Seconds since the last successful poll of the runtime", &|state| {
+            state.last_success.map_or(f64::INFINITY, |at| now.duration_since(at).as_secs_f64())
+        });
+        per_node(&mut out, "scrape_duration_seconds", MetricKind::Gauge, "Duration of the last poll of the runtime", &|state| {
+            state.poll_duration.as_secs_f64()
+        });
+        per_node(&mut out, "upstream_errors_total", MetricKind::Counter, "Polls of the runtime that failed", &|state| state.errors as f64);
+
+        let scrapes = exporter("scrapes_total");
+        write_family(&mut out, &scrapes, MetricKind::Counter, "Requests served at /metrics");
+        write_sample(&mut out, &scrapes, &[], self.scrapes as f64);
+        out
+    }
+}
+
+/// Polls the targets in the background and serves the registry until the process is stopped
+///
+/// With `echo` every poll is reported on standard output.
+pub fn run(grpc: GrpcConfig, targets: Vec<Target>, options: &ExporterOptions, echo: bool) -> Result<()> {
+    let listener = TcpListener::bind(&options.listen).with_context(|| format!("Failed to listen on {}", options.listen))?;
+    let registry = Arc::new(Mutex::new(MetricsRegistry::new(options.max_stale)));
+
+    let poller = Arc::clone(&registry);
+    let interval = options.interval;
+    std::thread::spawn(move || {
+        loop {
+            for target in &targets {
+                poll(&grpc, target, &poller, echo);
+            }
+            std::thread::sleep(interval);
+        }
+    });
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let registry = Arc::clone(&registry);
+        std::thread::spawn(move || {
+            if let Err(e) = handle_request(stream, &registry) {
+                tracing::debug!("Failed to answer scrape: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn poll(grpc: &GrpcConfig, target: &Target, registry: &Mutex<MetricsRegistry>, echo: bool) {
+    let started = Instant::now();
+    let result = call_vm_service_at(grpc, &target.address, GET_METRICS_METHOD, &json!({}));
+    let took = started.elapsed();
+
+    let mut registry = registry.lock().unwrap_or_else(|e| e.into_inner());
+    match result {
+        Ok(response) => {
+            let metrics = response["metrics"].as_array().map_or(&[][..], Vec::as_slice);
+            registry.record_success(&target.node, metrics, took);
+            if echo {
+                println!("[{}] {}: {} metrics in {} ms", chrono::Local::now().format("%H:%M:%S"), target.node, metrics.len(), took.as_millis());
+            }
+        }
+        Err(e) => {
+            registry.record_failure(&target.node, took);
+            if echo {
+                println!("[{}] {}: unreachable, serving last data ({})", chrono::Local::now().format("%H:%M:%S"), target.node, e);
+            } else {
+                eprintln!("Failed to poll {}: {}", target.node, e);
+            }
+        }
+    }
+}
+
+fn handle_request(mut stream: TcpStream, registry: &Mutex<MetricsRegistry>) -> Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The headers are not needed, but a client may wait until they have been read
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+    let path = path.split('?').next().unwrap_or_default();
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET" | "HEAD", "/metrics") => {
+            let body = registry.lock().unwrap_or_else(|e| e.into_inner()).render();
+            ("200 OK", CONTENT_TYPE, body)
+        }
+        ("GET" | "HEAD", "/") => ("200 OK", "text/plain", "DotLanth exporter; metrics are served at /metrics\n".to_string()),
+        ("GET" | "HEAD", _) => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "text/plain", "Method not allowed\n".to_string()),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    if method != "HEAD" {
+        stream.write_all(body.as_bytes())?;
+    }
+    stream.flush()?;
+    Ok(())
+}
+
+/// Value of the most recent data point; proto3 JSON encodes non-finite doubles as strings
+fn latest_value(metric: &Value) -> Option<f64> {
+    let value = &metric["dataPoints"].as_array()?.last()?["value"];
+    value.as_f64().or_else(|| value.as_str()?.parse().ok())
+}
+
+/// Replaces characters not allowed in Prometheus metric and label names
+fn sanitize(name: &str) -> String {
+    let mut sanitized: String = name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    if sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+fn write_family(out: &mut String, name: &str, kind: MetricKind, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help.replace('\\', "\\\\").replace('\n', "\\n"));
+    let _ = writeln!(out, "# TYPE {} {}", name, kind.name());
+}
+
+fn write_sample(out: &mut String, name: &str, labels: &[(String, String)], value: f64) {
+    out.push_str(name);
+    if !labels.is_empty() {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")))
+            .collect();
+        let _ = write!(out, "{{{}}}", labels.join(","));
+    }
+    let value = if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    };
+    let _ = writeln!(out, " {}", value);
+}
@@ -0,0 +1,284 @@
+//! Request bodies and replayable test sessions for gRPC endpoints
+//!
+//! Bodies edited in the TUI are stored per endpoint and test sessions as named lists
+//! of endpoint and body pairs, both as JSON files in the data directory. Calls go
+//! through grpcurl, which also provides request templates through server reflection.
+
+use crate::config::DotLanthConfig;
+use anyhow::{Context, Result, anyhow};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Instant;
+
+const REQUEST_BODIES_FILE: &str = "grpc_request_bodies.json";
+const TEST_SESSIONS_FILE: &str = "grpc_test_sessions.json";
+
+/// Key bodies are stored under and the form endpoints are given on the command line
+pub fn endpoint_key(service: &str, method: &str) -> String {
+    format!("{}/{}", service, method)
+}
+
+/// Split `service/method`
+pub fn parse_endpoint(endpoint: &str) -> Result<(String, String)> {
+    match endpoint.rsplit_once('/') {
+        Some((service, method)) if !service.is_empty() && !method.is_empty() => Ok((service.to_string(), method.to_string())),
+        _ => Err(anyhow!("Expected an endpoint of the form service/method, got '{}'", endpoint)),
+    }
+}
+
+fn read_json<T: Default + for<'de> Deserialize<'de>>(path: &Path) -> Result<T> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(value)?).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Request bodies saved per endpoint
+pub struct RequestBodies {
+    path: PathBuf,
+    bodies: BTreeMap<String, String>,
+}
+
+impl RequestBodies {
+    /// Bodies saved in `data_dir`; none if nothing has been saved yet
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(REQUEST_BODIES_FILE);
+        let bodies = read_json(&path)?;
+        Ok(Self { path, bodies })
+    }
+
+    /// An empty store that saves to `data_dir`, for when the saved bodies cannot be read
+    pub fn empty(data_dir: &Path) -> Self {
+        Self {
+            path: data_dir.join(REQUEST_BODIES_FILE),
+            bodies: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(&self, service: &str, method: &str) -> Option<&str> {
+        self.bodies.get(&endpoint_key(service, method)).map(String::as_str)
+    }
+
+    pub fn set(&mut self, service: &str, method: &str, body: String) -> Result<()> {
+        self.bodies.insert(endpoint_key(service, method), body);
+        write_json(&self.path, &self.bodies)
+    }
+}
+
+/// One call of a test session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionStep {
+    pub service: String,
+    pub method: String,
+    pub body: String,
+}
+
+/// Named test sessions, replayed step by step in order
+pub struct TestSessions {
+    path: PathBuf,
+    sessions: BTreeMap<String, Vec<SessionStep>>,
+}
+
+impl TestSessions {
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(TEST_SESSIONS_FILE);
+        let sessions = read_json(&path)?;
+        Ok(Self { path, sessions })
+    }
+
+    pub fn empty(data_dir: &Path) -> Self {
+        Self {
+            path: data_dir.join(TEST_SESSIONS_FILE),
+            sessions: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&[SessionStep]> {
+        self.sessions.get(name).map(Vec::as_slice)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[SessionStep])> {
+        self.sessions.iter().map(|(name, steps)| (name.as_str(), steps.as_slice()))
+    }
+
+    /// Add a step to the end of `name`, creating the session if needed; returns the step count
+    pub fn append(&mut self, name: &str, step: SessionStep) -> Result<usize> {
+        let steps = self.sessions.entry(name.to_string()).or_default();
+        steps.push(step);
+        let count = steps.len();
+        write_json(&self.path, &self.sessions)?;
+        Ok(count)
+    }
+
+    /// Delete a session, returning whether it existed
+    pub fn remove(&mut self, name: &str) -> Result<bool> {
+        let removed = self.sessions.remove(name).is_some();
+        if removed {
+            write_json(&self.path, &self.sessions)?;
+        }
+        Ok(removed)
+    }
+}
+
+/// Outcome of one grpcurl call
+#[derive(Debug, Clone)]
+pub struct Invocation {
+    pub success: bool,
+    pub response: String,
+    pub started_at: Instant,
+    pub duration_ms: u64,
+}
+
+/// Call `service/method` with `body`, sending `auth_token` if given
+pub fn invoke(config: &DotLanthConfig, auth_token: Option<&str>, service: &str, method: &str, body: &str) -> Invocation {
+    let started_at = Instant::now();
+
+    let mut cmd = Command::new("grpcurl");
+    let timeout_secs = (config.grpc.connection_timeout_ms / 1000).to_string();
+    cmd.args(config.grpc.grpcurl_transport_args());
+    cmd.args(["-max-time", &timeout_secs]);
+
+    // Note: grpcurl doesn't have -4 flag, so we rely on using 127.0.0.1 instead of localhost
+
+    if let Some(token) = auth_token {
+        // Try both common auth header formats
+        cmd.args(["-H", &format!("Authorization: Bearer {}", token)]);
+        cmd.args(["-H", &format!("x-api-key: {}", token)]);
+        cmd.args(["-H", &format!("api-key: {}", token)]);
+    }
+
+    // Always send a body; an empty one is sent as empty JSON
+    cmd.args(["-d", if body.trim().is_empty() { "{}" } else { body }]);
+
+    let grpc_addr = format!("{}:{}", config.grpc.client_host, config.grpc.client_port);
+    cmd.args([&grpc_addr, &endpoint_key(service, method)]);
+
+    let result = cmd.output();
+    let duration_ms = started_at.elapsed().as_millis() as u64;
+
+    let (success, response) = match result {
+        Ok(output) if output.status.success() => {
+            let response = String::from_utf8_lossy(&output.stdout);
+            let clean_response = response.trim();
+
+            // Check if response is empty or contains error indicators
+            if clean_response.is_empty() {
+                (false, "Empty response received".to_string())
+            } else if clean_response.contains("RST_STREAM") || clean_response.contains("CANCEL") {
+                (false, format!("Connection error: {}", clean_response))
+            } else {
+                (true, clean_response.to_string())
+            }
+        }
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stdout = String::from_utf8_lossy(&output.stdout);
+
+            // Combine stderr and stdout for better error reporting
+            let error_msg = if !stderr.trim().is_empty() { stderr.trim() } else { stdout.trim() };
+            (false, format!("Error: {}", error_msg))
+        }
+        Err(e) => (false, format!("Command failed: {}", e)),
+    };
+
+    Invocation {
+        success,
+        response,
+        started_at,
+        duration_ms,
+    }
+}
+
+/// Run a grpcurl `describe` against the server's reflection service
+fn describe(config: &DotLanthConfig, symbol: &str, msg_template: bool) -> Result<String> {
+    let grpc_addr = format!("{}:{}", config.grpc.client_host, config.grpc.client_port);
+    let timeout_secs = (config.grpc.connection_timeout_ms / 1000).max(1).to_string();
+
+    let mut cmd = Command::new("grpcurl");
+    cmd.args(config.grpc.grpcurl_transport_args());
+    cmd.args(["-max-time", &timeout_secs]);
+    if msg_template {
+        cmd.arg("-msg-template");
+    }
+    cmd.args([grpc_addr.as_str(), "describe", symbol]);
+
+    let output = cmd.output().context("Failed to run grpcurl; is it installed and on PATH?")?;
+    if !output.status.success() {
+        return Err(anyhow!("describe {} failed: {}", symbol, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Request body for `service/method` with every field of its request message
+///
+/// The message layout comes from the server's descriptors through reflection; values
+/// from `example` are kept wherever a field of the same name exists.
+pub fn request_template(config: &DotLanthConfig, service: &str, method: &str, example: &str) -> Result<String> {
+    let method_description = describe(config, &format!("{}.{}", service, method), false)?;
+    let request_type = parse_request_type(&method_description).ok_or_else(|| anyhow!("Could not find the request type of {}/{}", service, method))?;
+
+    let type_description = describe(config, &request_type, true)?;
+    let template = parse_message_template(&type_description).ok_or_else(|| anyhow!("grpcurl returned no template for {}", request_type))?;
+
+    let filled = match serde_json::from_str::<Value>(example) {
+        Ok(example) => fill_template(&template, &example),
+        Err(_) => template,
+    };
+    Ok(serde_json::to_string_pretty(&filled)?)
+}
+
+/// Fully qualified request type from a method description such as
+/// `rpc ExecuteDot ( .vm_service.ExecuteDotRequest ) returns ( ... );`
+fn parse_request_type(description: &str) -> Option<String> {
+    let pattern = Regex::new(r"rpc\s+\w+\s*\(\s*(?:stream\s+)?\.?([\w.]+)\s*\)").ok()?;
+    pattern.captures(description).map(|captures| captures[1].to_string())
+}
+
+/// JSON following grpcurl's `Message template:` line
+fn parse_message_template(description: &str) -> Option<Value> {
+    let (_, template) = description.split_once("Message template:")?;
+    serde_json::from_str(template.trim()).ok()
+}
+
+/// Proto JSON accepts both the field name and its lowerCamelCase form
+fn normalize_field(name: &str) -> String {
+    name.chars().filter(|c| *c != '_').flat_map(char::to_lowercase).collect()
+}
+
+/// Replace template values with the example's values for fields present in both
+pub fn fill_template(template: &Value, example: &Value) -> Value {
+    let (Value::Object(template_fields), Value::Object(example_fields)) = (template, example) else {
+        return example.clone();
+    };
+    // grpcurl templates a map as a single entry with an empty key; the example's entries replace it
+    if template_fields.contains_key("") {
+        return example.clone();
+    }
+
+    let mut filled = Map::new();
+    for (name, template_value) in template_fields {
+        let example_value = example_fields
+            .iter()
+            .find(|(example_name, _)| normalize_field(example_name) == normalize_field(name))
+            .map(|(_, value)| value);
+        let value = match example_value {
+            Some(example_value) if template_value.is_object() && example_value.is_object() => fill_template(template_value, example_value),
+            Some(example_value) => example_value.clone(),
+            None => template_value.clone(),
+        };
+        filled.insert(name.clone(), value);
+    }
+    Value::Object(filled)
+}
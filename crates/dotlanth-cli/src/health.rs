@@ -0,0 +1,172 @@
+use crate::config::HealthConfig;
+use crate::database::{DotLanthDatabase, NodeInfo, NodeStatus};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Health RPC exposed by every runtime node
+pub const HEALTH_METHOD: &str = "vm_service.VmService/HealthCheck";
+
+/// Transitions kept per node; older ones are dropped first
+const MAX_TRANSITIONS: usize = 20;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HealthState {
+    /// Not probed yet, or failing but still under the failure threshold since registration
+    #[default]
+    Unknown,
+    Healthy,
+    Unhealthy,
+}
+
+impl HealthState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HealthState::Unknown => "unknown",
+            HealthState::Healthy => "healthy",
+            HealthState::Unhealthy => "unhealthy",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthTransition {
+    pub at: DateTime<Utc>,
+    pub from: HealthState,
+    pub to: HealthState,
+    pub reason: String,
+}
+
+/// Probe history of a node, folded from individual probe results
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NodeHealth {
+    pub state: HealthState,
+    pub consecutive_failures: u32,
+    /// Round-trip time of the last successful probe
+    pub latency_ms: Option<u64>,
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub transitions: Vec<HealthTransition>,
+}
+
+impl NodeHealth {
+    /// Applies a probe result. A single success marks the node healthy; it only becomes
+    /// unhealthy after `failure_threshold` consecutive failures. Returns the transition, if any.
+    pub fn record(&mut self, result: &ProbeResult, failure_threshold: u32) -> Option<HealthTransition> {
+        let (next, reason) = match result {
+            Ok(latency) => {
+                self.consecutive_failures = 0;
+                self.latency_ms = Some(latency.as_millis() as u64);
+                self.last_success = Some(Utc::now());
+                self.last_error = None;
+                (HealthState::Healthy, format!("probe succeeded in {} ms", latency.as_millis()))
+            }
+            Err(error) => {
+                self.consecutive_failures += 1;
+                self.last_error = Some(error.clone());
+                let next = if self.consecutive_failures >= failure_threshold.max(1) { HealthState::Unhealthy } else { self.state };
+                (next, format!("{} consecutive failures, last: {}", self.consecutive_failures, error))
+            }
+        };
+
+        if next == self.state {
+            return None;
+        }
+
+        let transition = HealthTransition {
+            at: Utc::now(),
+            from: self.state,
+            to: next,
+            reason,
+        };
+        self.state = next;
+        self.transitions.push(transition.clone());
+        let excess = self.transitions.len().saturating_sub(MAX_TRANSITIONS);
+        if excess > 0 {
+            self.transitions.drain(0..excess);
+        }
+        Some(transition)
+    }
+}
+
+/// Probe round-trip time, or why the node did not answer as serving
+pub type ProbeResult = Result<Duration, String>;
+
+/// Calls a node's gRPC health endpoint through `grpcurl`, like the TUI connection test
+#[derive(Debug, Clone, Copy)]
+pub struct HealthProbe {
+    pub timeout: Duration,
+}
+
+impl HealthProbe {
+    pub fn from_config(config: &HealthConfig) -> Self {
+        Self {
+            timeout: Duration::from_millis(config.probe_timeout_ms),
+        }
+    }
+
+    pub fn probe(&self, address: &str) -> ProbeResult {
+        let target = address.trim_start_matches("http://").trim_start_matches("https://");
+        let max_time = format!("{:.3}", self.timeout.as_secs_f64());
+
+        let started = Instant::now();
+        let output = std::process::Command::new("grpcurl")
+            .args(["-plaintext", "-max-time", &max_time, "-d", "{}", target, HEALTH_METHOD])
+            .output()
+            .map_err(|e| format!("grpcurl not found: {}", e))?;
+        let latency = started.elapsed();
+
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            return Err(error.trim().lines().last().unwrap_or("health check failed").to_string());
+        }
+
+        // grpcurl omits zero-valued enums, so a missing status is HEALTH_UNKNOWN
+        let response: serde_json::Value = serde_json::from_slice(&output.stdout).map_err(|e| format!("invalid health response: {}", e))?;
+        match response.get("overallStatus").and_then(|s| s.as_str()) {
+            Some("HEALTH_SERVING") => Ok(latency),
+            Some(status) => Err(format!("node reported {}", status)),
+            None => Err("node reported HEALTH_UNKNOWN".to_string()),
+        }
+    }
+}
+
+/// Node status implied by a health state; maintenance is an operator decision and is kept
+pub fn status_for(node: &NodeInfo, health: &NodeHealth) -> NodeStatus {
+    match (&node.status, health.state) {
+        (NodeStatus::Maintenance, _) => NodeStatus::Maintenance,
+        (_, HealthState::Healthy) => NodeStatus::Online,
+        (_, HealthState::Unhealthy) => NodeStatus::Error(health.last_error.clone().unwrap_or_default()),
+        (status, HealthState::Unknown) => status.clone(),
+    }
+}
+
+/// Probes one node and stores the result, returning the transition it caused
+pub fn probe_node(database: &DotLanthDatabase, node: &NodeInfo, probe: &HealthProbe, failure_threshold: u32) -> Result<(ProbeResult, Option<HealthTransition>)> {
+    let result = probe.probe(&node.address);
+    let mut health = node.health.clone();
+    let transition = health.record(&result, failure_threshold);
+    let status = status_for(node, &health);
+    database.update_node_health(&node.id, health, status)?;
+    Ok((result, transition))
+}
+
+/// Probes every registered node in parallel, returning the transitions of this round by node ID
+pub fn probe_all(database: &DotLanthDatabase, config: &HealthConfig) -> Result<Vec<(String, HealthTransition)>> {
+    let probe = HealthProbe::from_config(config);
+    let nodes = database.list_nodes()?;
+
+    let results: Vec<Result<(ProbeResult, Option<HealthTransition>)>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = nodes.iter().map(|node| scope.spawn(|| probe_node(database, node, &probe, config.failure_threshold))).collect();
+        handles.into_iter().map(|h| h.join().unwrap_or_else(|_| Err(anyhow::anyhow!("probe thread panicked")))).collect()
+    });
+
+    let mut transitions = Vec::new();
+    for (node, result) in nodes.iter().zip(results) {
+        if let (_, Some(transition)) = result? {
+            transitions.push((node.id.clone(), transition));
+        }
+    }
+    Ok(transitions)
+}
@@ -1,20 +1,28 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use dotvm_core::bytecode::VmArchitecture;
 use std::path::PathBuf;
+use std::time::Duration;
 
+mod backup;
 mod commands;
 mod config;
 mod database;
+mod deployment;
+mod exporter;
+mod grpc_tester;
+mod health;
 mod tui;
 
 use crate::commands::CommandContext;
-use crate::config::DotLanthConfig;
+use crate::config::{CliOverrides, DotLanthConfig};
+use crate::exporter::ExporterOptions;
 use anyhow::Result;
 
 /// CLI for DotLanth infrastructure management
 #[derive(Parser, Debug)]
 #[command(name = "dotlanth", about = "DotLanth Infrastructure Management")]
 pub struct Cli {
-    /// Path to configuration file (TOML)
+    /// Path to configuration file (TOML, or YAML for .yaml/.yml)
     #[arg(long)]
     pub config: Option<PathBuf>,
 
@@ -22,10 +30,108 @@ pub struct Cli {
     #[arg(long)]
     pub data_dir: Option<PathBuf>,
 
+    /// Talk to the runtime in plaintext even when TLS is configured (local development only)
+    #[arg(long)]
+    pub insecure: bool,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
+/// Polling options of the metrics exporter
+#[derive(Args, Debug)]
+pub struct ExporterArgs {
+    /// Seconds between polls of the runtime
+    #[arg(long, default_value_t = 15)]
+    pub interval_secs: u64,
+    /// Keep serving a runtime's last metrics for this many seconds after it stops answering
+    #[arg(long, default_value_t = 300)]
+    pub max_stale_secs: u64,
+    /// Poll every registered node instead of the configured runtime
+    #[arg(long)]
+    pub all_nodes: bool,
+}
+
+impl ExporterArgs {
+    fn options(&self, listen: String) -> ExporterOptions {
+        ExporterOptions {
+            listen,
+            interval: Duration::from_secs(self.interval_secs),
+            max_stale: Duration::from_secs(self.max_stale_secs),
+        }
+    }
+}
+
+/// Project template of `dotlanth new`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum DotTemplate {
+    /// A counter with one state key, the smallest complete dot
+    #[default]
+    Basic,
+    /// A vault with several state keys, roles and an owner-only operation
+    Stateful,
+    /// A handler for requests the REST gateway routes to the dot
+    HttpHandler,
+}
+
+/// Options of `dotlanth new`
+#[derive(Args, Debug)]
+pub struct NewArgs {
+    /// Name of the dot, also the Cargo package name
+    #[arg(required_unless_present = "list_templates")]
+    pub name: Option<String>,
+    /// Template to generate the project from
+    #[arg(long, value_enum, default_value_t)]
+    pub template: DotTemplate,
+    /// Directory to generate into instead of ./<name>
+    #[arg(long)]
+    pub path: Option<PathBuf>,
+    /// Generate into a directory that is not empty, overwriting files the template provides
+    #[arg(long)]
+    pub force: bool,
+    /// List the templates with their descriptions and exit
+    #[arg(long)]
+    pub list_templates: bool,
+}
+
+/// Format selected by `--output`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// One JSON document on standard output, or one per line while following
+    Json,
+}
+
+/// Filters of a query against the centralized log store
+#[derive(Args, Debug)]
+pub struct LogsArgs {
+    /// Only entries of this dot
+    #[arg(long)]
+    pub dot: Option<String>,
+    /// Minimum level: error, warn, info, debug or trace
+    #[arg(long)]
+    pub level: Option<String>,
+    /// Start of the time range: RFC 3339 or an age such as 15m, 2h or 1d
+    #[arg(long)]
+    pub since: Option<String>,
+    /// End of the time range, in the same forms as --since
+    #[arg(long)]
+    pub until: Option<String>,
+    /// Regular expression the message must match
+    #[arg(long)]
+    pub grep: Option<String>,
+    /// Most entries to show; the newest are kept
+    #[arg(long, default_value_t = 100)]
+    pub limit: u32,
+    /// Keep printing new matching entries as they arrive
+    #[arg(long, short = 'f')]
+    pub follow: bool,
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+}
+
 /// Subcommands for node management
 #[derive(Subcommand, Debug)]
 #[command(about = "Manage individual nodes (add/remove/list)")]
@@ -36,16 +142,24 @@ pub enum NodeCommands {
     Add { addr: String },
     /// Remove an existing node by ID
     Remove { node_id: String },
+    /// Stop deploying to a node; its existing deployments keep running
+    Drain { node_id: String },
 }
 
 /// Subcommands for cluster operations
 #[derive(Subcommand, Debug)]
 #[command(about = "Cluster-wide operations and scaling")]
 pub enum ClusterCommands {
-    /// Show cluster status
-    Status,
+    /// Probe every node and show cluster status
+    Status {
+        /// Keep probing on the configured interval and report health transitions
+        #[arg(long)]
+        watch: bool,
+    },
     /// Scale the cluster to a given number of replicas
     Scale { count: u32 },
+    /// Show the read replicas following the runtime's document store and how far each lags
+    Replicas,
 }
 
 /// Subcommands for backup and restore
@@ -53,9 +167,25 @@ pub enum ClusterCommands {
 #[command(about = "Backup and restore infrastructure state")]
 pub enum BackupCommands {
     /// Create a new backup with the given name
-    Create { name: String },
-    /// Restore from a backup by name
+    Create {
+        name: String,
+        /// Copy only the blocks that changed since a base backup
+        #[arg(long)]
+        incremental: bool,
+        /// Base of an incremental backup; defaults to the most recent backup
+        #[arg(long, requires = "incremental")]
+        base: Option<String>,
+    },
+    /// Restore from a backup by name, verifying every block first
     Restore { name: String },
+    /// Check every block of a backup, or of all backups, against its checksum
+    Verify { name: Option<String> },
+    /// Delete all but the most recent backups, keeping the bases they depend on
+    Prune {
+        /// Number of most recent backups to retain
+        #[arg(long)]
+        keep_last: usize,
+    },
 }
 
 /// Subcommands for configuration inspection and update
@@ -63,9 +193,171 @@ pub enum BackupCommands {
 #[command(about = "Inspect or update CLI configuration")]
 pub enum ConfigCommands {
     /// Show current effective configuration
-    Show,
+    Show {
+        /// Print every effective value with where it came from
+        #[arg(long)]
+        origins: bool,
+    },
     /// Update a configuration key to a new value
-    Set { key: String, value: String },
+    Set {
+        key: String,
+        value: String,
+        /// Apply a `storage.*` setting to the running node's storage engine without a restart
+        #[arg(long)]
+        live: bool,
+    },
+    /// Check a TOML or YAML config file without starting anything
+    Validate {
+        /// Config file to check; defaults to --config or $DOTLANTH_CONFIG
+        path: Option<PathBuf>,
+    },
+}
+
+/// Subcommands for per-dot resource quotas
+#[derive(Subcommand, Debug)]
+#[command(about = "Inspect or update per-dot resource quotas")]
+pub enum QuotaCommands {
+    /// Show a dot's quota, current usage and rejection count
+    Get { dot_id: String },
+    /// Update a dot's quota; omitted limits are left unchanged
+    Set {
+        dot_id: String,
+        /// Maximum memory in MB held by the dot's running tasks
+        #[arg(long)]
+        memory_mb: Option<u64>,
+        /// Maximum CPU cores held by the dot's running tasks
+        #[arg(long)]
+        cpu_cores: Option<f32>,
+        /// Maximum number of concurrently running tasks
+        #[arg(long)]
+        max_tasks: Option<u32>,
+    },
+}
+
+/// Subcommands for memoized results of deterministic dots
+#[derive(Subcommand, Debug)]
+#[command(about = "Inspect or toggle result caching of deterministic dots")]
+pub enum CacheCommands {
+    /// Show hit rate and memory use of the result cache
+    Stats {
+        /// Only show this dot
+        #[arg(long)]
+        dot_id: Option<String>,
+    },
+    /// Serve repeated executions of a deterministic dot from the cache
+    Enable { dot_id: String },
+    /// Execute a dot every time and drop its cached results
+    Disable { dot_id: String },
+}
+
+/// Subcommands for checkpoints taken around redeploys and migrations
+#[derive(Subcommand, Debug)]
+#[command(about = "List or restore checkpoints taken around risky operations")]
+pub enum CheckpointsCommands {
+    /// List retained checkpoints, newest first
+    List {
+        /// Only list this category: dot_redeploy, storage_schema or config_migration
+        #[arg(long)]
+        category: Option<String>,
+    },
+    /// Put back the state a checkpoint captured
+    Restore { checkpoint_id: String },
+}
+
+/// Subcommands for per-dot memory tracking and leak detection
+#[derive(Subcommand, Debug)]
+#[command(about = "Inspect per-dot memory usage and leak suspects")]
+pub enum MemoryCommands {
+    /// Show live allocation counts and bytes per dot
+    Usage {
+        /// Only show this dot
+        #[arg(long)]
+        dot_id: Option<String>,
+    },
+    /// List allocations older than the runtime's leak age
+    Leaks {
+        /// Only show this dot
+        #[arg(long)]
+        dot_id: Option<String>,
+        /// Print the captured allocation site for each suspect
+        #[arg(long)]
+        show_sites: bool,
+    },
+    /// Change tracking settings on the running VM; omitted settings are left unchanged
+    Tracking {
+        /// Start recording allocations
+        #[arg(long, conflicts_with = "disable")]
+        enable: bool,
+        /// Stop recording allocations and drop existing records
+        #[arg(long)]
+        disable: bool,
+        /// Capture a backtrace per allocation (on/off)
+        #[arg(long, value_parser = clap::builder::BoolishValueParser::new())]
+        backtraces: Option<bool>,
+        /// Age in seconds after which a live allocation is reported as a leak suspect
+        #[arg(long)]
+        leak_age_secs: Option<u64>,
+    },
+}
+
+/// Subcommands for saved gRPC test sessions
+#[derive(Subcommand, Debug)]
+#[command(about = "Replay saved sequences of gRPC calls")]
+pub enum TestSessionCommands {
+    /// Call every step of a session in order and report pass/fail and latency
+    Run { name: String },
+    /// List saved sessions
+    List,
+    /// Print a session's steps and request bodies
+    Show { name: String },
+    /// Add a call to the end of a session, creating it if needed
+    Add {
+        name: String,
+        /// Endpoint to call, as service/method
+        endpoint: String,
+        /// Request body (JSON); defaults to the body saved for the endpoint in the TUI
+        #[arg(long)]
+        body: Option<String>,
+    },
+    /// Delete a session
+    Remove { name: String },
+}
+
+/// Scope granted to a gateway API key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ApiKeyScope {
+    /// Read-only requests
+    Read,
+    /// Executing dots
+    Execute,
+    /// Deploying and deleting dots
+    Deploy,
+    /// Everything, including key management
+    Admin,
+}
+
+/// Subcommands for gateway API keys
+#[derive(Subcommand, Debug)]
+#[command(about = "Create, list, rotate or revoke gateway API keys")]
+pub enum ApiKeyCommands {
+    /// Create a key and print its secret, which is not shown again
+    Create {
+        /// Name identifying the key in listings
+        #[arg(long)]
+        name: String,
+        /// Scope granted to the key; repeat for several
+        #[arg(long = "scope", value_enum, required = true)]
+        scopes: Vec<ApiKeyScope>,
+        /// Only allow the key to reach this dot; repeat for several
+        #[arg(long = "dot")]
+        dots: Vec<String>,
+    },
+    /// List keys with their prefix, scopes and when they were created and last used
+    List,
+    /// Replace a key with a new secret; the old one keeps working for the gateway's grace period
+    Rotate { id: String },
+    /// Revoke a key
+    Revoke { id: String },
 }
 
 /// Top-level commands for dotlanth
@@ -77,17 +369,55 @@ pub enum Commands {
     /// Display current infrastructure status
     Status,
 
-    /// Deploy a dot file to the cluster
+    /// Deploy a dot file, or a directory of dot files in dependency order, to the cluster
     Deploy {
-        /// Path to the .dot file to deploy
-        dot_file: PathBuf,
+        /// Path to the .dot file to deploy, or a directory of .dot files
+        path: PathBuf,
+        /// Remove the partially deployed dot if activation fails
+        #[arg(long)]
+        rollback_on_failure: bool,
+        /// Stop after validation and print the generated ABI
+        #[arg(long)]
+        dry_run: bool,
+        /// Retry on the next healthy node when the target node is unhealthy
+        #[arg(long)]
+        failover: bool,
+        /// Architecture to transpile for; warns when the runtime defaults to another
+        #[arg(long, default_value = "arch64")]
+        architecture: VmArchitecture,
+    },
+
+    /// Delete a deployed dot from the runtime
+    Delete {
+        dot_id: String,
+        /// Delete even if other dots depend on it
+        #[arg(long)]
+        force: bool,
     },
 
     /// Stream real-time metrics and logs
-    Monitor,
+    Monitor {
+        /// Serve the runtime's metrics for Prometheus at this address instead, e.g. 0.0.0.0:9464
+        #[arg(long)]
+        listen: Option<String>,
+        #[command(flatten)]
+        exporter: ExporterArgs,
+    },
+
+    /// Serve the runtime's metrics at /metrics in the Prometheus text format
+    Exporter {
+        /// Address to serve metrics at
+        #[arg(long, default_value = "0.0.0.0:9464")]
+        listen: String,
+        #[command(flatten)]
+        exporter: ExporterArgs,
+    },
 
-    /// View centralized logs from the cluster
-    Logs,
+    /// Query centralized logs from the cluster
+    Logs {
+        #[command(flatten)]
+        query: LogsArgs,
+    },
 
     /// Manage individual nodes (add/remove/list)
     Nodes {
@@ -112,13 +442,76 @@ pub enum Commands {
         #[command(subcommand)]
         command: ConfigCommands,
     },
+
+    /// Manage per-dot resource quotas
+    Quota {
+        #[command(subcommand)]
+        command: QuotaCommands,
+    },
+
+    /// Manage result caching of deterministic dots
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommands,
+    },
+
+    /// List or restore checkpoints taken before redeploys and migrations
+    Checkpoints {
+        #[command(subcommand)]
+        command: CheckpointsCommands,
+    },
+
+    /// Inspect per-dot memory usage and detect leaks
+    Memory {
+        #[command(subcommand)]
+        command: MemoryCommands,
+    },
+
+    /// Replay saved gRPC test sessions
+    TestSession {
+        #[command(subcommand)]
+        command: TestSessionCommands,
+    },
+
+    /// Manage API keys of the REST gateway
+    Apikey {
+        #[command(subcommand)]
+        command: ApiKeyCommands,
+    },
+
+    /// Generate a dot project from a built-in template
+    New(NewArgs),
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
+    // Validation must not depend on the config it is checking
+    if let Commands::Config {
+        command: ConfigCommands::Validate { path },
+    } = &cli.command
+    {
+        let path = path
+            .clone()
+            .or_else(|| cli.config.clone())
+            .or_else(|| std::env::var_os("DOTLANTH_CONFIG").map(PathBuf::from))
+            .ok_or_else(|| anyhow::anyhow!("No config file given; pass a path, --config or set $DOTLANTH_CONFIG"))?;
+        return commands::config::validate_config(&path);
+    }
+
+    // Scaffolding needs neither a config nor a data directory
+    if let Commands::New(args) = &cli.command {
+        return commands::new::handle_new_command(args);
+    }
+
     // Load configuration
-    let config = DotLanthConfig::resolve_config(cli.config, cli.data_dir)?;
+    let config = DotLanthConfig::resolve_config(
+        cli.config,
+        CliOverrides {
+            data_dir: cli.data_dir,
+            insecure: cli.insecure,
+        },
+    )?;
 
     // Create command context
     let ctx = CommandContext::new(config)?;
@@ -131,14 +524,35 @@ fn main() -> Result<()> {
         Commands::Status => {
             commands::cluster::show_status(&ctx)?;
         }
-        Commands::Deploy { dot_file } => {
-            commands::deploy::deploy_dot(&ctx, &dot_file)?;
+        Commands::Deploy {
+            path,
+            rollback_on_failure,
+            dry_run,
+            failover,
+            architecture,
+        } => {
+            let options = commands::deploy::DeployOptions {
+                rollback_on_failure,
+                dry_run,
+                failover,
+                architecture,
+            };
+            commands::deploy::deploy(&ctx, &path, options)?;
         }
-        Commands::Monitor => {
+        Commands::Delete { dot_id, force } => {
+            commands::deploy::delete_dot(&ctx, &dot_id, force)?;
+        }
+        Commands::Monitor { listen: None, .. } => {
             commands::monitor::start_monitoring(&ctx)?;
         }
-        Commands::Logs => {
-            commands::monitor::show_logs(&ctx)?;
+        Commands::Monitor { listen: Some(listen), exporter } => {
+            commands::monitor::serve_metrics(&ctx, &exporter.options(listen), exporter.all_nodes, true)?;
+        }
+        Commands::Exporter { listen, exporter } => {
+            commands::monitor::serve_metrics(&ctx, &exporter.options(listen), exporter.all_nodes, false)?;
+        }
+        Commands::Logs { query } => {
+            commands::logs::show_logs(&ctx, &query)?;
         }
         Commands::Nodes { command } => {
             commands::nodes::handle_node_command(&ctx, command)?;
@@ -152,6 +566,25 @@ fn main() -> Result<()> {
         Commands::Config { command } => {
             commands::config::handle_config_command(&ctx, command)?;
         }
+        Commands::Quota { command } => {
+            commands::quota::handle_quota_command(&ctx, command)?;
+        }
+        Commands::Cache { command } => {
+            commands::cache::handle_cache_command(&ctx, command)?;
+        }
+        Commands::Checkpoints { command } => {
+            commands::checkpoints::handle_checkpoints_command(&ctx, command)?;
+        }
+        Commands::Memory { command } => {
+            commands::memory::handle_memory_command(&ctx, command)?;
+        }
+        Commands::TestSession { command } => {
+            commands::test_session::handle_test_session_command(&ctx, command)?;
+        }
+        Commands::Apikey { command } => {
+            commands::apikey::handle_apikey_command(&ctx, command)?;
+        }
+        Commands::New(_) => unreachable!("dispatched before the configuration is loaded"),
     }
 
     Ok(())
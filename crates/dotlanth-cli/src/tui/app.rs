@@ -1,8 +1,15 @@
 use crate::commands::CommandContext;
-use crate::database::{DeploymentInfo, LogEntry, MetricEntry, NodeInfo};
+use crate::database::{DeploymentInfo, MetricEntry, NodeInfo};
+use crate::grpc_tester::{self, RequestBodies, SessionStep, TestSessions};
+use crate::tui::body_editor::{EditorTarget, TextEditor};
+use crate::tui::log_view::LogView;
+use crate::tui::paradots::ParaDotTree;
 use anyhow::Result;
 use std::time::{Duration, Instant};
 
+/// How often the Overview tab asks the VM for its ParaDots; each poll runs grpcurl
+const PARADOT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
 #[derive(Debug, Clone)]
 pub struct RequestMetrics {
     pub total_requests: u64,
@@ -139,6 +146,24 @@ impl GrpcEndpointManager {
                 requires_auth: true,
                 example_request: r#"{"bytecode": "0x01020304", "target_architecture": "WASM"}"#.to_string(),
             },
+            GrpcEndpoint {
+                service: "vm_service.VmService".to_string(),
+                method: "SpawnParaDot".to_string(),
+                requires_auth: true,
+                example_request: r#"{"parent_dot_id": "test-dot-id", "name": "ingest", "template": "DataProcessor", "limits": {"max_memory_mb": 256}}"#.to_string(),
+            },
+            GrpcEndpoint {
+                service: "vm_service.VmService".to_string(),
+                method: "ListParaDots".to_string(),
+                requires_auth: true,
+                example_request: r#"{"filter": {"parent_dot_id": "test-dot-id"}}"#.to_string(),
+            },
+            GrpcEndpoint {
+                service: "vm_service.VmService".to_string(),
+                method: "TerminateParaDot".to_string(),
+                requires_auth: true,
+                example_request: r#"{"paradot_id": "paradot-id", "graceful": true}"#.to_string(),
+            },
         ];
 
         // Streaming and Advanced gRPC Features
@@ -303,7 +328,8 @@ pub struct App {
     pub current_tab: TabIndex,
     pub nodes: Vec<NodeInfo>,
     pub deployments: Vec<DeploymentInfo>,
-    pub logs: Vec<LogEntry>,
+    pub log_view: LogView,
+    pub last_log_poll: Instant,
     pub metrics: Vec<MetricEntry>,
     pub scroll_offset: usize,
     pub show_help: bool,
@@ -319,16 +345,38 @@ pub struct App {
     pub request_metrics: RequestMetrics,
     pub grpc_server_logs: Vec<String>,
     pub show_grpc_logs: bool,
+    /// Request bodies edited per endpoint, used instead of the endpoint's example
+    pub request_bodies: RequestBodies,
+    pub test_sessions: TestSessions,
+    /// Open editor popup; it captures all keys while open
+    pub editor: Option<TextEditor>,
+    /// ParaDots per dot shown on the Overview tab
+    pub paradot_tree: ParaDotTree,
+    /// Why the last ParaDot poll failed
+    pub paradot_error: Option<String>,
+    pub last_paradot_poll: Option<Instant>,
 }
 
 impl App {
     pub fn new(context: CommandContext) -> Self {
+        let log_buffer_size = context.config.ui.log_buffer_size;
+        let data_dir = context.config.data_dir.clone();
+        let mut load_errors = Vec::new();
+        let request_bodies = RequestBodies::load(&data_dir).unwrap_or_else(|e| {
+            load_errors.push(e.to_string());
+            RequestBodies::empty(&data_dir)
+        });
+        let test_sessions = TestSessions::load(&data_dir).unwrap_or_else(|e| {
+            load_errors.push(e.to_string());
+            TestSessions::empty(&data_dir)
+        });
         let mut app = Self {
             context,
             current_tab: TabIndex::Overview,
             nodes: Vec::new(),
             deployments: Vec::new(),
-            logs: Vec::new(),
+            log_view: LogView::new(log_buffer_size),
+            last_log_poll: Instant::now(),
             metrics: Vec::new(),
             scroll_offset: 0,
             show_help: false,
@@ -344,10 +392,18 @@ impl App {
             request_metrics: RequestMetrics::new(),
             grpc_server_logs: Vec::new(),
             show_grpc_logs: false,
+            request_bodies,
+            test_sessions,
+            editor: None,
+            paradot_tree: ParaDotTree::default(),
+            paradot_error: None,
+            last_paradot_poll: None,
         };
 
         if let Err(e) = app.refresh_data() {
             app.status_message = format!("Error loading data: {}", e);
+        } else if !load_errors.is_empty() {
+            app.status_message = format!("Error loading saved requests: {}", load_errors.join("; "));
         }
 
         app
@@ -356,7 +412,7 @@ impl App {
     pub fn refresh_data(&mut self) -> Result<()> {
         self.nodes = self.context.database.list_nodes()?;
         self.deployments = self.context.database.list_deployments()?;
-        self.logs = self.context.database.get_recent_logs(None, self.context.config.ui.max_log_lines)?;
+        self.poll_logs()?;
         self.metrics = self.context.database.get_recent_metrics(None, self.context.config.ui.max_log_lines)?;
         self.last_update = Instant::now();
         self.status_message = format!("Data refreshed at {}", chrono::Local::now().format("%H:%M:%S"));
@@ -412,122 +468,181 @@ impl App {
 
     pub fn update(&mut self) {
         self.last_update = Instant::now();
+
+        if self.last_log_poll.elapsed() >= Duration::from_millis(self.context.config.ui.refresh_rate_ms)
+            && let Err(e) = self.poll_logs()
+        {
+            self.status_message = format!("Error loading logs: {}", e);
+        }
+
+        if self.current_tab == TabIndex::Overview && self.last_paradot_poll.is_none_or(|polled| polled.elapsed() >= PARADOT_POLL_INTERVAL) {
+            self.poll_paradots();
+        }
     }
 
-    pub fn test_endpoint_sync(&mut self, endpoint: &GrpcEndpoint) -> Result<(), Box<dyn std::error::Error>> {
-        let start_time = Instant::now();
-        self.request_metrics.total_requests += 1;
+    /// Fetch the ParaDots of every dot from the VM status
+    pub fn poll_paradots(&mut self) {
+        self.last_paradot_poll = Some(Instant::now());
+        let invocation = grpc_tester::invoke(&self.context.config, self.auth_token.as_deref(), "vm_service.VmService", "GetVMStatus", r#"{"include_details": true}"#);
+        let tree = if invocation.success {
+            ParaDotTree::from_status(&invocation.response).map_err(|e| e.to_string())
+        } else {
+            Err(invocation.response)
+        };
+        match tree {
+            Ok(tree) => {
+                self.paradot_tree = tree;
+                self.paradot_error = None;
+            }
+            Err(e) => self.paradot_error = Some(e),
+        }
+    }
+
+    /// Append logs stored since the last poll to the Logs tab buffer
+    pub fn poll_logs(&mut self) -> Result<()> {
+        self.last_log_poll = Instant::now();
+        let new_logs = self.context.database.get_logs_after(self.log_view.last_id())?;
+        for log in new_logs {
+            self.log_view.push(log);
+        }
+        Ok(())
+    }
 
-        let mut cmd = std::process::Command::new("grpcurl");
-        let timeout_secs = (self.context.config.grpc.connection_timeout_ms / 1000).to_string();
-        cmd.args(&["-plaintext", "-max-time", &timeout_secs]);
+    /// Body sent when testing `endpoint`: the saved one if it was edited, otherwise its example
+    pub fn request_body(&self, endpoint: &GrpcEndpoint) -> String {
+        self.request_bodies
+            .get(&endpoint.service, &endpoint.method)
+            .map_or_else(|| endpoint.example_request.clone(), str::to_string)
+    }
 
-        // Note: grpcurl doesn't have -4 flag, so we rely on using 127.0.0.1 instead of localhost
+    pub fn test_endpoint_sync(&mut self, endpoint: &GrpcEndpoint) -> Result<(), Box<dyn std::error::Error>> {
+        self.request_metrics.total_requests += 1;
 
-        // Add auth header if required
-        if endpoint.requires_auth {
-            if let Some(token) = &self.auth_token {
-                // Try both common auth header formats
-                cmd.args(&["-H", &format!("Authorization: Bearer {}", token)]);
-                cmd.args(&["-H", &format!("x-api-key: {}", token)]);
-                cmd.args(&["-H", &format!("api-key: {}", token)]);
-            } else {
+        let auth_token = if endpoint.requires_auth {
+            if self.auth_token.is_none() {
                 // If auth required but no token, show warning but still try request
                 self.status_message = "Warning: Auth required but no token set. Press 'A' to set token.".to_string();
             }
-        }
+            self.auth_token.as_deref()
+        } else {
+            None
+        };
 
-        // Add request data - always include for consistency
-        if !endpoint.example_request.is_empty() {
-            cmd.args(&["-d", &endpoint.example_request]);
+        let body = self.request_body(endpoint);
+        let invocation = grpc_tester::invoke(&self.context.config, auth_token, &endpoint.service, &endpoint.method, &body);
+
+        // Update metrics
+        self.request_metrics.add_request(invocation.duration_ms);
+        if invocation.success {
+            self.request_metrics.successful_requests += 1;
         } else {
-            // For empty requests, explicitly send empty JSON
-            cmd.args(&["-d", "{}"]);
+            self.request_metrics.failed_requests += 1;
         }
 
-        // Use configured gRPC address for cross-platform compatibility
-        let grpc_addr = format!("{}:{}", self.context.config.grpc.client_host, self.context.config.grpc.client_port);
-        cmd.args(&[&grpc_addr, &format!("{}/{}", endpoint.service, endpoint.method)]);
+        self.grpc_endpoint_manager.test_results.push(TestResult {
+            endpoint: grpc_tester::endpoint_key(&endpoint.service, &endpoint.method),
+            success: invocation.success,
+            response: invocation.response,
+            timestamp: invocation.started_at,
+            duration_ms: invocation.duration_ms,
+        });
 
-        // Execute command
-        let result = cmd.output();
-        let duration = start_time.elapsed();
-        let duration_ms = duration.as_millis() as u64;
+        // Keep only last 50 results
+        if self.grpc_endpoint_manager.test_results.len() > 50 {
+            self.grpc_endpoint_manager.test_results.remove(0);
+        }
 
-        // Update metrics
-        self.request_metrics.add_request(duration_ms);
-
-        let test_result = match result {
-            Ok(output) if output.status.success() => {
-                let response = String::from_utf8_lossy(&output.stdout);
-                let clean_response = response.trim();
-
-                // Check if response is empty or contains error indicators
-                if clean_response.is_empty() {
-                    self.request_metrics.failed_requests += 1;
-                    TestResult {
-                        endpoint: format!("{}/{}", endpoint.service, endpoint.method),
-                        success: false,
-                        response: "Empty response received".to_string(),
-                        timestamp: start_time,
-                        duration_ms,
-                    }
-                } else if clean_response.contains("RST_STREAM") || clean_response.contains("CANCEL") {
-                    self.request_metrics.failed_requests += 1;
-                    TestResult {
-                        endpoint: format!("{}/{}", endpoint.service, endpoint.method),
-                        success: false,
-                        response: format!("Connection error: {}", clean_response),
-                        timestamp: start_time,
-                        duration_ms,
-                    }
-                } else {
-                    self.request_metrics.successful_requests += 1;
-                    TestResult {
-                        endpoint: format!("{}/{}", endpoint.service, endpoint.method),
-                        success: true,
-                        response: clean_response.to_string(),
-                        timestamp: start_time,
-                        duration_ms,
-                    }
+        Ok(())
+    }
+
+    /// Edit the selected endpoint's request body
+    ///
+    /// Starts from the saved body, or else from a template of the request message
+    /// fetched through server reflection, falling back to the endpoint's example.
+    pub fn open_body_editor(&mut self) {
+        let Some(endpoint) = self.grpc_endpoint_manager.get_selected_endpoint().cloned() else {
+            return;
+        };
+
+        let body = match self.request_bodies.get(&endpoint.service, &endpoint.method) {
+            Some(body) => body.to_string(),
+            None => match grpc_tester::request_template(&self.context.config, &endpoint.service, &endpoint.method, &endpoint.example_request) {
+                Ok(template) => template,
+                Err(e) => {
+                    self.status_message = format!("No template from reflection ({}); starting from the example", e);
+                    pretty_json(&endpoint.example_request)
                 }
-            }
-            Ok(output) => {
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                let stdout = String::from_utf8_lossy(&output.stdout);
-
-                // Combine stderr and stdout for better error reporting
-                let error_msg = if !stderr.trim().is_empty() { stderr.trim() } else { stdout.trim() };
-
-                self.request_metrics.failed_requests += 1;
-                TestResult {
-                    endpoint: format!("{}/{}", endpoint.service, endpoint.method),
-                    success: false,
-                    response: format!("Error: {}", error_msg),
-                    timestamp: start_time,
-                    duration_ms,
+            },
+        };
+
+        self.editor = Some(TextEditor::new(
+            EditorTarget::RequestBody {
+                service: endpoint.service,
+                method: endpoint.method,
+            },
+            &body,
+        ));
+    }
+
+    /// Ask for the name of a test session to add the selected endpoint and its body to
+    pub fn open_session_prompt(&mut self) {
+        let Some(endpoint) = self.grpc_endpoint_manager.get_selected_endpoint().cloned() else {
+            return;
+        };
+        let body = self.request_body(&endpoint);
+        self.editor = Some(TextEditor::new(
+            EditorTarget::SessionName {
+                service: endpoint.service,
+                method: endpoint.method,
+                body,
+            },
+            "",
+        ));
+    }
+
+    /// Save the editor's text; invalid input keeps the editor open with an error
+    pub fn commit_editor(&mut self) {
+        let Some(mut editor) = self.editor.take() else {
+            return;
+        };
+        let text = editor.text();
+        let target = editor.target.clone();
+
+        let result = match &target {
+            EditorTarget::RequestBody { service, method } => match serde_json::from_str::<serde_json::Value>(&text) {
+                Ok(_) => self.request_bodies.set(service, method, text).map(|()| format!("Saved request body for {}/{}", service, method)),
+                Err(e) => {
+                    editor.set_error(format!("Invalid JSON: {}", e));
+                    self.editor = Some(editor);
+                    return;
                 }
-            }
-            Err(e) => {
-                self.request_metrics.failed_requests += 1;
-                TestResult {
-                    endpoint: format!("{}/{}", endpoint.service, endpoint.method),
-                    success: false,
-                    response: format!("Command failed: {}", e),
-                    timestamp: start_time,
-                    duration_ms,
+            },
+            EditorTarget::SessionName { service, method, body } => {
+                let name = text.trim();
+                if name.is_empty() {
+                    editor.set_error("Session name cannot be empty".to_string());
+                    self.editor = Some(editor);
+                    return;
                 }
+                let step = SessionStep {
+                    service: service.clone(),
+                    method: method.clone(),
+                    body: body.clone(),
+                };
+                self.test_sessions
+                    .append(name, step)
+                    .map(|steps| format!("Added {} to session '{}' ({} steps); run it with `dotlanth test-session run {}`", method, name, steps, name))
             }
         };
 
-        self.grpc_endpoint_manager.test_results.push(test_result);
-
-        // Keep only last 50 results
-        if self.grpc_endpoint_manager.test_results.len() > 50 {
-            self.grpc_endpoint_manager.test_results.remove(0);
-        }
+        self.status_message = match result {
+            Ok(message) => message,
+            Err(e) => format!("Save failed: {}", e),
+        };
+    }
 
-        Ok(())
+    pub fn cancel_editor(&mut self) {
+        self.editor = None;
     }
 
     // gRPC Server management functions
@@ -625,7 +740,10 @@ impl App {
         self.status_message = "Testing gRPC connection...".to_string();
 
         let grpc_addr = format!("{}:{}", self.context.config.grpc.client_host, self.context.config.grpc.client_port);
-        let output = std::process::Command::new("grpcurl").args(&["-plaintext", &grpc_addr, "list"]).output();
+        let output = std::process::Command::new("grpcurl")
+            .args(self.context.config.grpc.grpcurl_transport_args())
+            .args([&grpc_addr, "list"])
+            .output();
 
         match output {
             Ok(result) if result.status.success() => {
@@ -697,3 +815,9 @@ impl App {
         }
     }
 }
+
+fn pretty_json(json: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(json)
+        .and_then(|value| serde_json::to_string_pretty(&value))
+        .unwrap_or_else(|_| json.to_string())
+}
@@ -0,0 +1,159 @@
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+
+/// What the editor popup is editing
+#[derive(Debug, Clone)]
+pub enum EditorTarget {
+    /// The request body sent when testing an endpoint
+    RequestBody { service: String, method: String },
+    /// The name of the test session an endpoint and its body are added to
+    SessionName { service: String, method: String, body: String },
+}
+
+/// Text editor shown as a popup over the gRPC Endpoints tab
+///
+/// Request bodies are edited over multiple lines; session names are a single line.
+pub struct TextEditor {
+    pub target: EditorTarget,
+    lines: Vec<String>,
+    row: usize,
+    /// Cursor position within the row, in characters
+    col: usize,
+    error: Option<String>,
+}
+
+impl TextEditor {
+    pub fn new(target: EditorTarget, text: &str) -> Self {
+        let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+        if lines.is_empty() {
+            lines.push(String::new());
+        }
+        let row = lines.len() - 1;
+        let col = lines[row].chars().count();
+        Self { target, lines, row, col, error: None }
+    }
+
+    pub fn multiline(&self) -> bool {
+        matches!(self.target, EditorTarget::RequestBody { .. })
+    }
+
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    /// Show `error` until the next edit
+    pub fn set_error(&mut self, error: String) {
+        self.error = Some(error);
+    }
+
+    fn byte_index(&self) -> usize {
+        self.lines[self.row].char_indices().nth(self.col).map_or(self.lines[self.row].len(), |(index, _)| index)
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        let index = self.byte_index();
+        self.lines[self.row].insert(index, c);
+        self.col += 1;
+        self.error = None;
+    }
+
+    pub fn newline(&mut self) {
+        if !self.multiline() {
+            return;
+        }
+        let index = self.byte_index();
+        let rest = self.lines[self.row].split_off(index);
+        self.lines.insert(self.row + 1, rest);
+        self.row += 1;
+        self.col = 0;
+        self.error = None;
+    }
+
+    pub fn backspace(&mut self) {
+        if self.col > 0 {
+            self.col -= 1;
+            let index = self.byte_index();
+            self.lines[self.row].remove(index);
+        } else if self.row > 0 {
+            let line = self.lines.remove(self.row);
+            self.row -= 1;
+            self.col = self.lines[self.row].chars().count();
+            self.lines[self.row].push_str(&line);
+        }
+        self.error = None;
+    }
+
+    pub fn move_left(&mut self) {
+        if self.col > 0 {
+            self.col -= 1;
+        } else if self.row > 0 {
+            self.row -= 1;
+            self.col = self.lines[self.row].chars().count();
+        }
+    }
+
+    pub fn move_right(&mut self) {
+        if self.col < self.lines[self.row].chars().count() {
+            self.col += 1;
+        } else if self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.col = 0;
+        }
+    }
+
+    pub fn move_up(&mut self) {
+        if self.row > 0 {
+            self.row -= 1;
+            self.col = self.col.min(self.lines[self.row].chars().count());
+        }
+    }
+
+    pub fn move_down(&mut self) {
+        if self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.col = self.col.min(self.lines[self.row].chars().count());
+        }
+    }
+
+    pub fn home(&mut self) {
+        self.col = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.col = self.lines[self.row].chars().count();
+    }
+}
+
+pub fn render_editor(f: &mut Frame<'_>, editor: &TextEditor, area: Rect) {
+    let (title, area) = match &editor.target {
+        EditorTarget::RequestBody { service, method } => (format!("Request body: {}/{} - Ctrl+S: save | Esc: cancel", service, method), area),
+        EditorTarget::SessionName { method, .. } => {
+            // A name needs one line; keep the popup at the top of the given area
+            let height = area.height.min(if editor.error.is_some() { 4 } else { 3 });
+            (format!("Add {} to test session - Enter: add | Esc: cancel", method), Rect { height, ..area })
+        }
+    };
+
+    let border_color = if editor.error.is_some() { Color::Red } else { Color::Yellow };
+    let block = Block::default().borders(Borders::ALL).title(title).border_style(Style::default().fg(border_color));
+    let inner = block.inner(area);
+
+    // Keep the error on the last row and scroll the text so the cursor stays visible
+    let text_height = inner.height.saturating_sub(u16::from(editor.error.is_some())).max(1) as usize;
+    let scroll = (editor.row + 1).saturating_sub(text_height);
+
+    let mut lines: Vec<Line> = editor.lines.iter().skip(scroll).take(text_height).map(|line| Line::from(line.as_str())).collect();
+    if let Some(error) = &editor.error {
+        lines.resize(text_height, Line::from(""));
+        lines.push(Line::from(Span::styled(error.as_str(), Style::default().fg(Color::Red))));
+    }
+
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(block), area);
+    f.set_cursor(inner.x + editor.col.min(inner.width.saturating_sub(1) as usize) as u16, inner.y + (editor.row - scroll) as u16);
+}
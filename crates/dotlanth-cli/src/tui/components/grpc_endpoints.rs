@@ -196,7 +196,7 @@ fn render_endpoints(f: &mut Frame, app: &App, area: Rect) {
 }
 
 fn render_endpoint_controls(f: &mut Frame, app: &App, area: Rect) {
-    let controls_text = "Enter: Test | ←→: Categories | ↑↓: Endpoints | A: Auth Token";
+    let controls_text = "Enter: Test | e: Edit Body | p: Add to Session | ←→: Categories | ↑↓: Endpoints | A: Auth Token";
 
     let controls = Paragraph::new(controls_text)
         .style(Style::default().fg(Color::Gray))
@@ -230,12 +230,14 @@ fn render_endpoint_details(f: &mut Frame, app: &App, area: Rect) {
     let endpoint = app.grpc_endpoint_manager.get_selected_endpoint();
 
     let details_text = if let Some(ep) = endpoint {
-        let formatted_request = format_json_for_display(&ep.example_request);
+        let saved = app.request_bodies.get(&ep.service, &ep.method).is_some();
+        let formatted_request = format_json_for_display(&app.request_body(ep));
         format!(
-            "Service: {}\nMethod: {}\nAuth Required: {}\nExample Request (JSON):\n{}",
+            "Service: {}\nMethod: {}\nAuth Required: {}\n{} (JSON):\n{}",
             ep.service,
             ep.method,
             if ep.requires_auth { "Yes" } else { "No" },
+            if saved { "Saved Request" } else { "Example Request" },
             formatted_request
         )
     } else {
@@ -0,0 +1,239 @@
+use crate::database::LogEntry;
+use regex::{Regex, RegexBuilder};
+use std::collections::{HashSet, VecDeque};
+
+/// Levels that can be toggled in the Logs tab, with their hotkeys
+pub const LOG_LEVELS: [(&str, char); 4] = [("ERROR", 'E'), ("WARN", 'W'), ("INFO", 'I'), ("DEBUG", 'D')];
+
+/// Compiled form of the filter query
+enum FilterPattern {
+    /// Case-insensitive substring, stored lowercased
+    Substring(String),
+    Regex(Regex),
+}
+
+impl FilterPattern {
+    /// Byte ranges of every match in `text`
+    fn find_ranges(&self, text: &str) -> Vec<(usize, usize)> {
+        match self {
+            FilterPattern::Substring(needle) => {
+                // ASCII lowercasing keeps byte offsets aligned with the original text
+                let haystack = text.to_ascii_lowercase();
+                haystack.match_indices(needle.as_str()).map(|(start, m)| (start, start + m.len())).collect()
+            }
+            FilterPattern::Regex(regex) => regex.find_iter(text).filter(|m| !m.is_empty()).map(|m| (m.start(), m.end())).collect(),
+        }
+    }
+
+    fn is_match(&self, text: &str) -> bool {
+        match self {
+            FilterPattern::Substring(needle) => text.to_ascii_lowercase().contains(needle.as_str()),
+            FilterPattern::Regex(regex) => regex.is_match(text),
+        }
+    }
+}
+
+/// Log history for the Logs tab: a bounded ring buffer plus the filter, follow and
+/// scroll state used to view it. Filtering never drops entries, so clearing the
+/// filter restores the full history.
+pub struct LogView {
+    entries: VecDeque<LogEntry>,
+    capacity: usize,
+    query: String,
+    regex_mode: bool,
+    pattern: Option<FilterPattern>,
+    filter_error: Option<String>,
+    hidden_levels: HashSet<String>,
+    /// Tail new entries and keep the view pinned to the bottom
+    pub follow: bool,
+    /// Whether the `/` filter input box has focus
+    pub input_active: bool,
+    input_backup: (String, bool),
+    scroll: usize,
+    viewport_height: usize,
+}
+
+impl LogView {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity: capacity.max(1),
+            query: String::new(),
+            regex_mode: false,
+            pattern: None,
+            filter_error: None,
+            hidden_levels: HashSet::new(),
+            follow: true,
+            input_active: false,
+            input_backup: (String::new(), false),
+            scroll: 0,
+            viewport_height: 0,
+        }
+    }
+
+    /// Append an entry, evicting the oldest one when the buffer is full
+    pub fn push(&mut self, entry: LogEntry) {
+        if self.entries.len() == self.capacity
+            && let Some(evicted) = self.entries.pop_front()
+            && self.matches(&evicted)
+        {
+            // Keep the rows on screen steady while scrolled back
+            self.scroll = self.scroll.saturating_sub(1);
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// All buffered entries, oldest first, ignoring the filter
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &LogEntry> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Id of the newest buffered entry, used to fetch only newer logs
+    pub fn last_id(&self) -> Option<&str> {
+        self.entries.back().map(|entry| entry.id.as_str())
+    }
+
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        !self.hidden_levels.contains(&entry.level) && self.pattern.as_ref().is_none_or(|pattern| pattern.is_match(&entry.message))
+    }
+
+    /// Entries passing the current filter, oldest first
+    pub fn visible(&self) -> Vec<&LogEntry> {
+        self.entries.iter().filter(|entry| self.matches(entry)).collect()
+    }
+
+    /// Byte ranges of `message` matched by the filter query, for highlighting
+    pub fn highlight_ranges(&self, message: &str) -> Vec<(usize, usize)> {
+        self.pattern.as_ref().map(|pattern| pattern.find_ranges(message)).unwrap_or_default()
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn regex_mode(&self) -> bool {
+        self.regex_mode
+    }
+
+    /// Error from compiling the current regex, if any
+    pub fn filter_error(&self) -> Option<&str> {
+        self.filter_error.as_deref()
+    }
+
+    pub fn level_hidden(&self, level: &str) -> bool {
+        self.hidden_levels.contains(level)
+    }
+
+    /// Show or hide the level bound to `hotkey` in [`LOG_LEVELS`]
+    pub fn toggle_level_hotkey(&mut self, hotkey: char) {
+        if let Some((level, _)) = LOG_LEVELS.iter().find(|(_, key)| *key == hotkey) {
+            if !self.hidden_levels.remove(*level) {
+                self.hidden_levels.insert(level.to_string());
+            }
+            self.scroll = 0;
+        }
+    }
+
+    pub fn toggle_follow(&mut self) {
+        self.follow = !self.follow;
+    }
+
+    /// Drop the query and show every level again
+    pub fn clear_filter(&mut self) {
+        self.query.clear();
+        self.hidden_levels.clear();
+        self.recompile();
+    }
+
+    /// Focus the filter input; edits apply live and `cancel_input` restores the previous query
+    pub fn begin_input(&mut self) {
+        self.input_active = true;
+        self.input_backup = (self.query.clone(), self.regex_mode);
+    }
+
+    pub fn input_char(&mut self, c: char) {
+        self.query.push(c);
+        self.recompile();
+    }
+
+    pub fn input_backspace(&mut self) {
+        self.query.pop();
+        self.recompile();
+    }
+
+    pub fn toggle_regex(&mut self) {
+        self.regex_mode = !self.regex_mode;
+        self.recompile();
+    }
+
+    pub fn commit_input(&mut self) {
+        self.input_active = false;
+    }
+
+    pub fn cancel_input(&mut self) {
+        self.input_active = false;
+        (self.query, self.regex_mode) = std::mem::take(&mut self.input_backup);
+        self.recompile();
+    }
+
+    fn recompile(&mut self) {
+        self.filter_error = None;
+        self.scroll = 0;
+        self.pattern = if self.query.is_empty() {
+            None
+        } else if self.regex_mode {
+            match RegexBuilder::new(&self.query).case_insensitive(true).build() {
+                Ok(regex) => Some(FilterPattern::Regex(regex)),
+                Err(e) => {
+                    self.filter_error = Some(e.to_string());
+                    None
+                }
+            }
+        } else {
+            Some(FilterPattern::Substring(self.query.to_ascii_lowercase()))
+        };
+    }
+
+    pub fn scroll_up(&mut self, rows: usize) {
+        if self.follow {
+            // Leaving the tail: start from the rows currently on screen
+            self.follow = false;
+            self.scroll = self.max_scroll();
+        }
+        self.scroll = self.scroll.saturating_sub(rows);
+    }
+
+    pub fn scroll_down(&mut self, rows: usize) {
+        if !self.follow {
+            self.scroll = (self.scroll + rows).min(self.max_scroll());
+        }
+    }
+
+    /// Record the list height and return the index of the first visible row to draw
+    pub fn window_start(&mut self, viewport_height: usize) -> usize {
+        self.viewport_height = viewport_height;
+        let max_scroll = self.max_scroll();
+        if self.follow {
+            max_scroll
+        } else {
+            self.scroll = self.scroll.min(max_scroll);
+            self.scroll
+        }
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.entries.iter().filter(|entry| self.matches(entry)).count().saturating_sub(self.viewport_height)
+    }
+}
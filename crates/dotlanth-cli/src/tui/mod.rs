@@ -1,5 +1,5 @@
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers},
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
@@ -7,8 +7,11 @@ use ratatui::{Terminal, backend::CrosstermBackend};
 use std::io;
 
 pub mod app;
+pub mod body_editor;
 pub mod components;
 pub mod events;
+pub mod log_view;
+pub mod paradots;
 pub mod ui;
 
 use crate::commands::CommandContext;
@@ -48,97 +51,142 @@ fn run_app<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, app: &mut A
         if event::poll(std::time::Duration::from_millis(100))? {
             if let Event::Key(key) = event::read()? {
                 if key.kind == KeyEventKind::Press {
-                    match key.code {
-                        // Global shortcuts
-                        KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
-                        KeyCode::Tab => app.next_tab(),
-                        KeyCode::BackTab => app.previous_tab(),
-                        KeyCode::Char('h') => app.toggle_help(),
-                        KeyCode::Char('d') => app.toggle_debug(),
-
-                        // Number key shortcuts (1-7)
-                        KeyCode::Char('1') => app.current_tab = app::TabIndex::Overview,
-                        KeyCode::Char('2') => app.current_tab = app::TabIndex::Nodes,
-                        KeyCode::Char('3') => app.current_tab = app::TabIndex::Deployments,
-                        KeyCode::Char('4') => app.current_tab = app::TabIndex::Metrics,
-                        KeyCode::Char('5') => app.current_tab = app::TabIndex::Logs,
-                        KeyCode::Char('6') => app.current_tab = app::TabIndex::GrpcServer,
-                        KeyCode::Char('7') => app.current_tab = app::TabIndex::GrpcEndpoints,
-
-                        // gRPC Server controls
-                        KeyCode::Char('s') | KeyCode::Char('S') if app.current_tab == app::TabIndex::GrpcServer => {
-                            if app.grpc_server_running {
-                                let _ = app.stop_grpc_server();
-                            } else {
-                                let _ = app.start_grpc_server();
+                    // The editor popup captures all keys while it is open
+                    if let Some(editor) = app.editor.as_mut() {
+                        match key.code {
+                            KeyCode::Esc => app.cancel_editor(),
+                            KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => app.commit_editor(),
+                            KeyCode::Enter if !editor.multiline() => app.commit_editor(),
+                            KeyCode::Enter => editor.newline(),
+                            KeyCode::Tab => {
+                                editor.insert_char(' ');
+                                editor.insert_char(' ');
                             }
+                            KeyCode::Backspace => editor.backspace(),
+                            KeyCode::Left => editor.move_left(),
+                            KeyCode::Right => editor.move_right(),
+                            KeyCode::Up => editor.move_up(),
+                            KeyCode::Down => editor.move_down(),
+                            KeyCode::Home => editor.home(),
+                            KeyCode::End => editor.end(),
+                            KeyCode::Char(c) => editor.insert_char(c),
+                            _ => {}
                         }
-                        KeyCode::Char('b') | KeyCode::Char('B') if app.current_tab == app::TabIndex::GrpcServer => {
-                            let _ = app.build_grpc_server();
-                        }
-                        KeyCode::Char('t') | KeyCode::Char('T') if app.current_tab == app::TabIndex::GrpcServer => {
-                            let _ = app.test_grpc_connection();
-                        }
-                        KeyCode::Char('r') | KeyCode::Char('R') if app.current_tab == app::TabIndex::GrpcServer => {
-                            let _ = app.restart_grpc_server();
-                        }
-                        KeyCode::Char('l') | KeyCode::Char('L') if app.current_tab == app::TabIndex::GrpcServer => {
-                            app.toggle_grpc_logs();
+                    // The log filter input captures all keys while it has focus
+                    } else if app.current_tab == app::TabIndex::Logs && app.log_view.input_active {
+                        match key.code {
+                            KeyCode::Enter => app.log_view.commit_input(),
+                            KeyCode::Esc => app.log_view.cancel_input(),
+                            KeyCode::Backspace => app.log_view.input_backspace(),
+                            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => app.log_view.toggle_regex(),
+                            KeyCode::Char(c) => app.log_view.input_char(c),
+                            _ => {}
                         }
+                    } else {
+                        match key.code {
+                            // Global shortcuts
+                            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                            KeyCode::Tab => app.next_tab(),
+                            KeyCode::BackTab => app.previous_tab(),
+                            KeyCode::Char('h') => app.toggle_help(),
+                            KeyCode::Char('d') => app.toggle_debug(),
 
-                        // gRPC Endpoints controls
-                        KeyCode::Left if app.current_tab == app::TabIndex::GrpcEndpoints => {
-                            app.grpc_endpoint_manager.previous_category();
-                        }
-                        KeyCode::Right if app.current_tab == app::TabIndex::GrpcEndpoints => {
-                            app.grpc_endpoint_manager.next_category();
-                        }
-                        KeyCode::Up if app.current_tab == app::TabIndex::GrpcEndpoints => {
-                            app.grpc_endpoint_manager.previous_endpoint();
-                        }
-                        KeyCode::Down if app.current_tab == app::TabIndex::GrpcEndpoints => {
-                            app.grpc_endpoint_manager.next_endpoint();
-                        }
-                        KeyCode::Enter if app.current_tab == app::TabIndex::GrpcEndpoints => {
-                            if let Some(endpoint) = app.grpc_endpoint_manager.get_selected_endpoint().cloned() {
-                                app.status_message = format!("Testing: {}/{} ...", endpoint.service, endpoint.method);
-                                if let Err(e) = app.test_endpoint_sync(&endpoint) {
-                                    app.status_message = format!("Test error: {}", e);
+                            // Number key shortcuts (1-7)
+                            KeyCode::Char('1') => app.current_tab = app::TabIndex::Overview,
+                            KeyCode::Char('2') => app.current_tab = app::TabIndex::Nodes,
+                            KeyCode::Char('3') => app.current_tab = app::TabIndex::Deployments,
+                            KeyCode::Char('4') => app.current_tab = app::TabIndex::Metrics,
+                            KeyCode::Char('5') => app.current_tab = app::TabIndex::Logs,
+                            KeyCode::Char('6') => app.current_tab = app::TabIndex::GrpcServer,
+                            KeyCode::Char('7') => app.current_tab = app::TabIndex::GrpcEndpoints,
+
+                            // gRPC Server controls
+                            KeyCode::Char('s') | KeyCode::Char('S') if app.current_tab == app::TabIndex::GrpcServer => {
+                                if app.grpc_server_running {
+                                    let _ = app.stop_grpc_server();
                                 } else {
-                                    let last_result = app.grpc_endpoint_manager.test_results.last();
-                                    if let Some(result) = last_result {
-                                        if result.success {
-                                            app.status_message = format!("✅ {}/{} ({}ms)", endpoint.service, endpoint.method, result.duration_ms);
-                                        } else {
-                                            app.status_message = format!("❌ {}/{} failed", endpoint.service, endpoint.method);
+                                    let _ = app.start_grpc_server();
+                                }
+                            }
+                            KeyCode::Char('b') | KeyCode::Char('B') if app.current_tab == app::TabIndex::GrpcServer => {
+                                let _ = app.build_grpc_server();
+                            }
+                            KeyCode::Char('t') | KeyCode::Char('T') if app.current_tab == app::TabIndex::GrpcServer => {
+                                let _ = app.test_grpc_connection();
+                            }
+                            KeyCode::Char('r') | KeyCode::Char('R') if app.current_tab == app::TabIndex::GrpcServer => {
+                                let _ = app.restart_grpc_server();
+                            }
+                            KeyCode::Char('l') | KeyCode::Char('L') if app.current_tab == app::TabIndex::GrpcServer => {
+                                app.toggle_grpc_logs();
+                            }
+
+                            // gRPC Endpoints controls
+                            KeyCode::Left if app.current_tab == app::TabIndex::GrpcEndpoints => {
+                                app.grpc_endpoint_manager.previous_category();
+                            }
+                            KeyCode::Right if app.current_tab == app::TabIndex::GrpcEndpoints => {
+                                app.grpc_endpoint_manager.next_category();
+                            }
+                            KeyCode::Up if app.current_tab == app::TabIndex::GrpcEndpoints => {
+                                app.grpc_endpoint_manager.previous_endpoint();
+                            }
+                            KeyCode::Down if app.current_tab == app::TabIndex::GrpcEndpoints => {
+                                app.grpc_endpoint_manager.next_endpoint();
+                            }
+                            KeyCode::Enter if app.current_tab == app::TabIndex::GrpcEndpoints => {
+                                if let Some(endpoint) = app.grpc_endpoint_manager.get_selected_endpoint().cloned() {
+                                    app.status_message = format!("Testing: {}/{} ...", endpoint.service, endpoint.method);
+                                    if let Err(e) = app.test_endpoint_sync(&endpoint) {
+                                        app.status_message = format!("Test error: {}", e);
+                                    } else {
+                                        let last_result = app.grpc_endpoint_manager.test_results.last();
+                                        if let Some(result) = last_result {
+                                            if result.success {
+                                                app.status_message = format!("✅ {}/{} ({}ms)", endpoint.service, endpoint.method, result.duration_ms);
+                                            } else {
+                                                app.status_message = format!("❌ {}/{} failed", endpoint.service, endpoint.method);
+                                            }
                                         }
                                     }
                                 }
                             }
-                        }
-                        KeyCode::Char('a') | KeyCode::Char('A') if app.current_tab == app::TabIndex::GrpcEndpoints => {
-                            app.auth_token = Some("test_api_key_12345".to_string());
-                            app.status_message = "Auth token set".to_string();
-                        }
-                        KeyCode::Char('x') | KeyCode::Char('X') if app.current_tab == app::TabIndex::GrpcEndpoints => {
-                            app.auth_token = None;
-                            app.status_message = "Auth token cleared".to_string();
-                        }
+                            KeyCode::Char('e') if app.current_tab == app::TabIndex::GrpcEndpoints => app.open_body_editor(),
+                            KeyCode::Char('p') if app.current_tab == app::TabIndex::GrpcEndpoints => app.open_session_prompt(),
+                            KeyCode::Char('a') | KeyCode::Char('A') if app.current_tab == app::TabIndex::GrpcEndpoints => {
+                                app.auth_token = Some("test_api_key_12345".to_string());
+                                app.status_message = "Auth token set".to_string();
+                            }
+                            KeyCode::Char('x') | KeyCode::Char('X') if app.current_tab == app::TabIndex::GrpcEndpoints => {
+                                app.auth_token = None;
+                                app.status_message = "Auth token cleared".to_string();
+                            }
+
+                            // Logs controls
+                            KeyCode::Char('/') if app.current_tab == app::TabIndex::Logs => app.log_view.begin_input(),
+                            KeyCode::Char('f') | KeyCode::Char('F') if app.current_tab == app::TabIndex::Logs => app.log_view.toggle_follow(),
+                            KeyCode::Char('c') | KeyCode::Char('C') if app.current_tab == app::TabIndex::Logs => app.log_view.clear_filter(),
+                            KeyCode::Char(c @ ('E' | 'W' | 'I' | 'D')) if app.current_tab == app::TabIndex::Logs => app.log_view.toggle_level_hotkey(c),
+                            KeyCode::Up if app.current_tab == app::TabIndex::Logs => app.log_view.scroll_up(1),
+                            KeyCode::Down if app.current_tab == app::TabIndex::Logs => app.log_view.scroll_down(1),
+                            KeyCode::PageUp if app.current_tab == app::TabIndex::Logs => app.log_view.scroll_up(5),
+                            KeyCode::PageDown if app.current_tab == app::TabIndex::Logs => app.log_view.scroll_down(5),
 
-                        // General navigation
-                        KeyCode::Up => app.scroll_up(),
-                        KeyCode::Down => app.scroll_down(),
-                        KeyCode::PageUp => {
-                            for _ in 0..5 {
-                                app.scroll_up();
+                            // General navigation
+                            KeyCode::Up => app.scroll_up(),
+                            KeyCode::Down => app.scroll_down(),
+                            KeyCode::PageUp => {
+                                for _ in 0..5 {
+                                    app.scroll_up();
+                                }
                             }
-                        }
-                        KeyCode::PageDown => {
-                            for _ in 0..5 {
-                                app.scroll_down();
+                            KeyCode::PageDown => {
+                                for _ in 0..5 {
+                                    app.scroll_down();
+                                }
                             }
+                            _ => {}
                         }
-                        _ => {}
                     }
                 }
             }
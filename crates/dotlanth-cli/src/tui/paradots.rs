@@ -0,0 +1,79 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// A ParaDot as listed under the dot that spawned it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParaDotNode {
+    pub id: String,
+    pub name: String,
+    pub paradot_type: String,
+    pub terminating: bool,
+    pub active_executions: u64,
+}
+
+impl ParaDotNode {
+    pub fn label(&self) -> String {
+        let state = if self.terminating { "terminating" } else { "running" };
+        let mut label = format!("{} [{}] {} {}", self.name, self.paradot_type, self.id, state);
+        if self.active_executions > 0 {
+            label.push_str(&format!(", {} executing", self.active_executions));
+        }
+        label
+    }
+}
+
+/// ParaDots grouped by parent dot, as reported by GetVMStatus with details
+#[derive(Debug, Clone, Default)]
+pub struct ParaDotTree {
+    pub parents: BTreeMap<String, Vec<ParaDotNode>>,
+}
+
+impl ParaDotTree {
+    /// Parse the JSON grpcurl prints for a GetVMStatus response
+    pub fn from_status(response: &str) -> Result<Self> {
+        let status: Value = serde_json::from_str(response).context("GetVMStatus did not return JSON")?;
+        let mut tree = Self::default();
+        // grpcurl leaves out empty fields, so a VM without ParaDots has no list at all
+        for paradot in field(&status, "paradots").and_then(Value::as_array).into_iter().flatten() {
+            let text = |name: &str| field(paradot, name).and_then(Value::as_str).unwrap_or_default().to_string();
+            let node = ParaDotNode {
+                id: text("paradotId"),
+                name: text("name"),
+                paradot_type: text("paradotType"),
+                terminating: text("state").ends_with("TERMINATING"),
+                active_executions: field(paradot, "activeExecutions").and_then(Value::as_u64).unwrap_or_default(),
+            };
+            tree.parents.entry(text("parentDotId")).or_default().push(node);
+        }
+        Ok(tree)
+    }
+
+    pub fn paradot_count(&self) -> usize {
+        self.parents.values().map(Vec::len).sum()
+    }
+
+    /// One line per parent dot followed by its ParaDots drawn as branches
+    pub fn lines(&self) -> Vec<(bool, String)> {
+        let mut lines = Vec::new();
+        for (parent, paradots) in &self.parents {
+            lines.push((true, parent.clone()));
+            for (i, paradot) in paradots.iter().enumerate() {
+                let branch = if i + 1 == paradots.len() { "└─" } else { "├─" };
+                lines.push((false, format!("{} {}", branch, paradot.label())));
+            }
+        }
+        lines
+    }
+}
+
+/// A field under its JSON name, or its proto name for output not produced by grpcurl
+fn field<'a>(value: &'a Value, json_name: &str) -> Option<&'a Value> {
+    value.get(json_name).or_else(|| {
+        let proto_name: String = json_name
+            .chars()
+            .flat_map(|c| if c.is_ascii_uppercase() { vec!['_', c.to_ascii_lowercase()] } else { vec![c] })
+            .collect();
+        value.get(proto_name)
+    })
+}
@@ -49,6 +49,10 @@ pub fn ui(f: &mut Frame<'_>, app: &mut App) {
     if app.show_debug {
         render_debug_popup(f, app, size);
     }
+
+    if let Some(editor) = &app.editor {
+        crate::tui::body_editor::render_editor(f, editor, centered_rect(70, 70, size));
+    }
 }
 
 fn render_header(f: &mut Frame<'_>, app: &App, area: Rect) {
@@ -75,7 +79,7 @@ fn render_header(f: &mut Frame<'_>, app: &App, area: Rect) {
     f.render_widget(tabs, area);
 }
 
-fn render_content(f: &mut Frame<'_>, app: &App, area: Rect) {
+fn render_content(f: &mut Frame<'_>, app: &mut App, area: Rect) {
     match app.current_tab {
         TabIndex::Overview => render_overview(f, app, area),
         TabIndex::Nodes => render_nodes(f, app, area),
@@ -144,8 +148,9 @@ fn render_overview(f: &mut Frame<'_>, app: &App, area: Rect) {
 
     // Recent activity
     let recent_logs: Vec<ListItem> = app
-        .logs
-        .iter()
+        .log_view
+        .entries()
+        .rev()
         .take(10)
         .map(|log| {
             let style = match log.level.as_str() {
@@ -158,8 +163,40 @@ fn render_overview(f: &mut Frame<'_>, app: &App, area: Rect) {
         })
         .collect();
 
+    let activity_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(chunks[1]);
+
+    render_paradot_tree(f, app, activity_chunks[0]);
+
     let recent_list = List::new(recent_logs).block(Block::default().borders(Borders::ALL).title("Recent Activity"));
-    f.render_widget(recent_list, chunks[1]);
+    f.render_widget(recent_list, activity_chunks[1]);
+}
+
+fn render_paradot_tree(f: &mut Frame<'_>, app: &App, area: Rect) {
+    let title = format!("ParaDots ({})", app.paradot_tree.paradot_count());
+    let items: Vec<ListItem> = if let Some(error) = &app.paradot_error {
+        vec![ListItem::new(Span::styled(format!("Unavailable: {}", error), Style::default().fg(Color::Red)))]
+    } else if app.paradot_tree.parents.is_empty() {
+        vec![ListItem::new(Span::styled("No ParaDots running", Style::default().fg(Color::Gray)))]
+    } else {
+        app.paradot_tree
+            .lines()
+            .into_iter()
+            .map(|(is_parent, line)| {
+                let style = if is_parent {
+                    Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                ListItem::new(Span::styled(line, style))
+            })
+            .collect()
+    };
+
+    let list = List::new(items).block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(list, area);
 }
 
 fn render_nodes(f: &mut Frame<'_>, app: &App, area: Rect) {
@@ -204,7 +241,7 @@ fn render_nodes(f: &mut Frame<'_>, app: &App, area: Rect) {
 }
 
 fn render_deployments(f: &mut Frame<'_>, app: &App, area: Rect) {
-    let header = Row::new(vec!["ID", "Dot Name", "Version", "Node", "Status"]).style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD));
+    let header = Row::new(vec!["ID", "Dot Name", "Version", "Node", "Status", "Stage"]).style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD));
 
     let rows: Vec<Row> = app
         .deployments
@@ -230,6 +267,7 @@ fn render_deployments(f: &mut Frame<'_>, app: &App, area: Rect) {
                 deployment.dot_version.clone(),
                 deployment.node_id.chars().take(12).collect::<String>(),
                 status_text.to_string(),
+                deployment.progress.as_ref().map(|p| p.summary()).unwrap_or_else(|| "-".to_string()),
             ])
             .style(status_style)
         })
@@ -239,7 +277,14 @@ fn render_deployments(f: &mut Frame<'_>, app: &App, area: Rect) {
         .header(header)
         .block(Block::default().borders(Borders::ALL).title("Deployments"))
         .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
-        .widths(&[Constraint::Length(15), Constraint::Length(20), Constraint::Length(10), Constraint::Length(15), Constraint::Length(10)]);
+        .widths(&[
+            Constraint::Length(15),
+            Constraint::Length(20),
+            Constraint::Length(10),
+            Constraint::Length(15),
+            Constraint::Length(10),
+            Constraint::Length(22),
+        ]);
 
     f.render_widget(table, area);
 }
@@ -370,35 +415,99 @@ fn render_grpc_server_tab(f: &mut Frame<'_>, app: &App, area: Rect) {
     f.render_widget(output, chunks[3]);
 }
 
-fn render_logs(f: &mut Frame<'_>, app: &App, area: Rect) {
-    let logs: Vec<ListItem> = app
-        .logs
+fn render_logs(f: &mut Frame<'_>, app: &mut App, area: Rect) {
+    let chunks = Layout::default().direction(Direction::Vertical).constraints([Constraint::Length(3), Constraint::Min(0)]).split(area);
+
+    render_log_filter_bar(f, app, chunks[0]);
+
+    let start = app.log_view.window_start(chunks[1].height.saturating_sub(2) as usize);
+    let log_view = &app.log_view;
+    let visible = log_view.visible();
+    let match_style = Style::default().fg(Color::Black).bg(Color::Yellow).add_modifier(Modifier::BOLD);
+
+    let logs: Vec<ListItem> = visible
         .iter()
-        .skip(app.scroll_offset)
-        .take(area.height as usize - 2)
+        .skip(start)
+        .take(chunks[1].height.saturating_sub(2) as usize)
         .map(|log| {
-            let style = match log.level.as_str() {
-                "ERROR" => Style::default().fg(Color::Red),
-                "WARN" => Style::default().fg(Color::Yellow),
-                "INFO" => Style::default().fg(Color::Green),
-                "DEBUG" => Style::default().fg(Color::Cyan),
-                _ => Style::default().fg(Color::Gray),
-            };
-
-            ListItem::new(Line::from(vec![
+            let mut spans = vec![
                 Span::styled(format!("[{}]", log.timestamp.format("%H:%M:%S")), Style::default().fg(Color::Gray)),
                 Span::raw(" "),
-                Span::styled(format!("[{}]", log.level), style),
+                Span::styled(format!("[{}]", log.level), log_level_style(&log.level)),
                 Span::raw(" "),
                 Span::styled(format!("[{}]", log.node_id.chars().take(8).collect::<String>()), Style::default().fg(Color::Blue)),
                 Span::raw(" "),
-                Span::raw(&log.message),
-            ]))
+            ];
+
+            // Split the message around filter matches so they can be highlighted
+            let mut last = 0;
+            for (match_start, match_end) in log_view.highlight_ranges(&log.message) {
+                spans.push(Span::raw(&log.message[last..match_start]));
+                spans.push(Span::styled(&log.message[match_start..match_end], match_style));
+                last = match_end;
+            }
+            spans.push(Span::raw(&log.message[last..]));
+
+            ListItem::new(Line::from(spans))
         })
         .collect();
 
-    let logs_list = List::new(logs).block(Block::default().borders(Borders::ALL).title("System Logs"));
-    f.render_widget(logs_list, area);
+    let title = format!(
+        "System Logs ({}/{} shown, buffer {}){}",
+        visible.len(),
+        log_view.len(),
+        log_view.capacity(),
+        if log_view.follow { " [following]" } else { "" }
+    );
+    let logs_list = List::new(logs).block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(logs_list, chunks[1]);
+}
+
+fn render_log_filter_bar(f: &mut Frame<'_>, app: &App, area: Rect) {
+    let log_view = &app.log_view;
+    let mode = if log_view.regex_mode() { "regex" } else { "text" };
+
+    let mut spans = if log_view.input_active {
+        vec![Span::raw("/"), Span::styled(log_view.query(), Style::default().fg(Color::White)), Span::styled("_", Style::default().add_modifier(Modifier::SLOW_BLINK))]
+    } else if log_view.query().is_empty() {
+        vec![Span::styled("Press / to filter", Style::default().fg(Color::DarkGray))]
+    } else {
+        vec![Span::raw("Filter: "), Span::styled(log_view.query(), Style::default().fg(Color::Yellow))]
+    };
+
+    spans.push(Span::raw("  |  "));
+    for (level, hotkey) in crate::tui::log_view::LOG_LEVELS {
+        let style = if log_view.level_hidden(level) {
+            Style::default().fg(Color::DarkGray).add_modifier(Modifier::CROSSED_OUT)
+        } else {
+            log_level_style(level)
+        };
+        spans.push(Span::styled(format!("{}:{} ", hotkey, level), style));
+    }
+
+    if let Some(error) = log_view.filter_error() {
+        spans.push(Span::styled(format!(" | invalid regex: {}", error.lines().last().unwrap_or(error)), Style::default().fg(Color::Red)));
+    }
+
+    let title = if log_view.input_active {
+        format!("Filter ({}) - Enter: apply | Esc: cancel | Ctrl+R: toggle regex", mode)
+    } else {
+        format!("Filter ({}) - /: edit | E/W/I/D: levels | f: follow | c: clear", mode)
+    };
+    let border_style = if log_view.input_active { Style::default().fg(Color::Yellow) } else { Style::default() };
+
+    let bar = Paragraph::new(Line::from(spans)).block(Block::default().borders(Borders::ALL).title(title).border_style(border_style));
+    f.render_widget(bar, area);
+}
+
+fn log_level_style(level: &str) -> Style {
+    match level {
+        "ERROR" => Style::default().fg(Color::Red),
+        "WARN" => Style::default().fg(Color::Yellow),
+        "INFO" => Style::default().fg(Color::Green),
+        "DEBUG" => Style::default().fg(Color::Cyan),
+        _ => Style::default().fg(Color::Gray),
+    }
 }
 
 fn render_footer(f: &mut Frame<'_>, app: &App, area: Rect) {
@@ -439,7 +548,8 @@ fn render_help_popup(f: &mut Frame<'_>, area: Rect) {
         Line::from("  Nodes            - Node management"),
         Line::from("  Deployments      - Deployment status"),
         Line::from("  Metrics          - Performance metrics"),
-        Line::from("  Logs             - System logs"),
+        Line::from("  Logs             - System logs (/ filter, f follow)"),
+        Line::from("  gRPC Endpoints   - Test endpoints (e edit body, p add to session)"),
         Line::from(""),
         Line::from("Press 'h' or 'Esc' to close this help."),
     ];
@@ -461,7 +571,7 @@ fn render_debug_popup(f: &mut Frame<'_>, app: &App, area: Rect) {
         Line::from(format!("Nodes: {}", app.nodes.len())),
         Line::from(format!("Deployments: {}", app.deployments.len())),
         Line::from(format!("Metrics: {}", app.metrics.len())),
-        Line::from(format!("Logs: {}", app.logs.len())),
+        Line::from(format!("Logs: {}", app.log_view.len())),
         Line::from(format!("Last Update: {:?}", app.last_update)),
         Line::from(""),
         Line::from("Press 'd' or 'Esc' to close."),
@@ -0,0 +1,30 @@
+//! The {{dot_name}} dot: a counter kept in dot state
+//!
+//! Every exported `extern "C"` function is an operation of the dot. Keep the
+//! operations in `abi.json` and `{{dot_name}}.dot` in step with the exports.
+
+#![cfg_attr(target_arch = "wasm32", no_std)]
+
+mod host;
+
+const COUNTER: &str = "counter";
+
+/// Adds `by` to the counter and returns the new value
+#[unsafe(no_mangle)]
+pub extern "C" fn increment(by: i32) -> i32 {
+    let value = host::read(COUNTER).wrapping_add(by);
+    host::write(COUNTER, value);
+    value
+}
+
+/// Returns the counter
+#[unsafe(no_mangle)]
+pub extern "C" fn get() -> i32 {
+    host::read(COUNTER)
+}
+
+#[cfg(target_arch = "wasm32")]
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    core::arch::wasm32::unreachable()
+}
@@ -0,0 +1,26 @@
+//! Bindings to the state host functions of the DotVM runtime
+//!
+//! Keys and values are passed as pointer and length pairs into the module's memory.
+//! Pass keys as string literals: the runtime derives the dot's state schema from
+//! constant keys, and reports any other key as unknown.
+
+#[link(wasm_import_module = "env")]
+unsafe extern "C" {
+    /// Reads the integer stored under the key, 0 if it is unset
+    fn get_state(key_ptr: *const u8, key_len: usize) -> i32;
+    /// Stores `value_len` bytes at `value_ptr` under the key
+    fn set_state(key_ptr: *const u8, key_len: usize, value_ptr: *const u8, value_len: usize);
+}
+
+/// Reads the integer stored under `key`, 0 if it is unset
+pub fn read(key: &str) -> i32 {
+    // SAFETY: the key is a valid slice for the duration of the call
+    unsafe { get_state(key.as_ptr(), key.len()) }
+}
+
+/// Stores `value` under `key`
+pub fn write(key: &str, value: i32) {
+    let bytes = value.to_le_bytes();
+    // SAFETY: the key and value are valid slices for the duration of the call
+    unsafe { set_state(key.as_ptr(), key.len(), bytes.as_ptr(), bytes.len()) }
+}
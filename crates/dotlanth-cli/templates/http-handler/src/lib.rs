@@ -0,0 +1,52 @@
+//! The {{dot_name}} dot: a handler for requests routed to it by the REST gateway
+//!
+//! Every exported `extern "C"` function is an operation of the dot. Keep the
+//! operations in `abi.json` and `{{dot_name}}.dot` in step with the exports.
+
+#![cfg_attr(target_arch = "wasm32", no_std)]
+
+mod host;
+
+const HITS: &str = "hits";
+const LAST_BODY_LEN: &str = "last_body_len";
+
+/// Request methods, as passed to `handle`
+const GET: i32 = 0;
+const POST: i32 = 1;
+const DELETE: i32 = 2;
+
+/// Largest request body accepted, in bytes
+const MAX_BODY_LEN: i32 = 64 * 1024;
+
+/// Handles a request with the given method and body length, returning the HTTP status
+#[unsafe(no_mangle)]
+pub extern "C" fn handle(method: i32, body_len: i32) -> i32 {
+    match method {
+        GET => {
+            host::write(HITS, host::read(HITS).wrapping_add(1));
+            200
+        }
+        POST if body_len > MAX_BODY_LEN => 413,
+        POST => {
+            host::write(LAST_BODY_LEN, body_len);
+            201
+        }
+        DELETE => {
+            host::write(HITS, 0);
+            204
+        }
+        _ => 405,
+    }
+}
+
+/// Returns how many GET requests were handled since the last DELETE
+#[unsafe(no_mangle)]
+pub extern "C" fn hits() -> i32 {
+    host::read(HITS)
+}
+
+#[cfg(target_arch = "wasm32")]
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    core::arch::wasm32::unreachable()
+}
@@ -0,0 +1,59 @@
+//! The {{dot_name}} dot: a vault holding a balance, with a withdrawal limit
+//!
+//! Every exported `extern "C"` function is an operation of the dot. Keep the
+//! operations in `abi.json` and `{{dot_name}}.dot` in step with the exports.
+
+#![cfg_attr(target_arch = "wasm32", no_std)]
+
+mod host;
+
+const BALANCE: &str = "balance";
+const LIMIT: &str = "limit";
+const WITHDRAWALS: &str = "withdrawals";
+
+/// Returned by `withdraw` when the balance or the limit does not allow it
+const REFUSED: i32 = -1;
+
+/// Adds `amount` to the balance and returns the new balance
+#[unsafe(no_mangle)]
+pub extern "C" fn deposit(amount: i32) -> i32 {
+    if amount <= 0 {
+        return host::read(BALANCE);
+    }
+    let balance = host::read(BALANCE).saturating_add(amount);
+    host::write(BALANCE, balance);
+    balance
+}
+
+/// Takes `amount` from the balance, returning the new balance or -1 if it is refused
+#[unsafe(no_mangle)]
+pub extern "C" fn withdraw(amount: i32) -> i32 {
+    let balance = host::read(BALANCE);
+    let limit = host::read(LIMIT);
+    if amount <= 0 || amount > balance || (limit > 0 && amount > limit) {
+        return REFUSED;
+    }
+    host::write(BALANCE, balance - amount);
+    host::write(WITHDRAWALS, host::read(WITHDRAWALS).saturating_add(1));
+    balance - amount
+}
+
+/// Returns the balance
+#[unsafe(no_mangle)]
+pub extern "C" fn balance() -> i32 {
+    host::read(BALANCE)
+}
+
+/// Sets the largest single withdrawal; 0 removes the limit
+#[unsafe(no_mangle)]
+pub extern "C" fn set_limit(limit: i32) -> i32 {
+    let limit = limit.max(0);
+    host::write(LIMIT, limit);
+    limit
+}
+
+#[cfg(target_arch = "wasm32")]
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    core::arch::wasm32::unreachable()
+}
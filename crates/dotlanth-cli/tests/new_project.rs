@@ -0,0 +1,84 @@
+use dotvm_tools::cli::transpile::ArchitectureArg;
+use dotvm_tools::{TranspilationPipeline, TranspileArgs};
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn dotlanth(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_dotlanth")).args(args).output().expect("dotlanth runs")
+}
+
+fn new_project(dir: &Path, extra: &[&str]) -> Output {
+    let mut args = vec!["new", "counter", "--path", dir.to_str().unwrap()];
+    args.extend_from_slice(extra);
+    dotlanth(&args)
+}
+
+/// Whether rustc can build for the target `dotvm transpile` compiles dots to
+fn wasm_target_installed() -> bool {
+    Command::new("rustc")
+        .args(["--print", "target-libdir", "--target", "wasm32-unknown-unknown"])
+        .output()
+        .is_ok_and(|output| output.status.success() && Path::new(String::from_utf8_lossy(&output.stdout).trim()).is_dir())
+}
+
+#[test]
+fn test_list_templates() {
+    let output = dotlanth(&["new", "--list-templates"]);
+    assert!(output.status.success());
+    let listing = String::from_utf8(output.stdout).unwrap();
+    let names: Vec<_> = listing.lines().filter_map(|line| line.split_whitespace().next()).collect();
+    assert_eq!(names, ["basic", "stateful", "http-handler"]);
+    assert!(listing.lines().all(|line| line.split_whitespace().count() > 1), "every template has a description:\n{}", listing);
+}
+
+#[test]
+fn test_non_empty_directory_needs_force() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = dir.path().join("counter");
+    std::fs::create_dir(&project).unwrap();
+    std::fs::write(project.join("notes.txt"), "keep me").unwrap();
+
+    let refused = new_project(&project, &[]);
+    assert!(!refused.status.success());
+    assert!(String::from_utf8_lossy(&refused.stderr).contains("--force"));
+    assert!(!project.join("Cargo.toml").exists());
+
+    let forced = new_project(&project, &["--force", "--template", "stateful"]);
+    assert!(forced.status.success(), "{}", String::from_utf8_lossy(&forced.stderr));
+    assert!(std::fs::read_to_string(project.join("src/lib.rs")).unwrap().contains("fn withdraw"));
+    assert_eq!(std::fs::read_to_string(project.join("notes.txt")).unwrap(), "keep me");
+}
+
+#[test]
+fn test_basic_template_transpiles() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = dir.path().join("counter");
+
+    let output = new_project(&project, &[]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    for file in ["Cargo.toml", "justfile", ".gitignore", "src/lib.rs", "src/host.rs", "abi.json", "counter.dot"] {
+        assert!(project.join(file).is_file(), "{} was not generated", file);
+    }
+    let abi: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(project.join("abi.json")).unwrap()).unwrap();
+    assert_eq!(abi["dot_name"], "counter");
+    assert!(std::fs::read_to_string(project.join("counter.dot")).unwrap().contains("dot counter {"));
+
+    if !wasm_target_installed() {
+        eprintln!("skipping transpilation: the wasm32-unknown-unknown target is not installed");
+        return;
+    }
+    // The same flags as the generated justfile
+    let bytecode = project.join("target/counter.dotvm");
+    let args = TranspileArgs {
+        input: project.clone(),
+        output: bytecode.clone(),
+        architecture: ArchitectureArg::Arch64,
+        opt_level: 2,
+        debug: false,
+        verbose: false,
+        keep_intermediate: false,
+        target_dir: None,
+    };
+    TranspilationPipeline::new(args).execute().expect("the basic template transpiles");
+    assert!(std::fs::metadata(&bytecode).unwrap().len() > 0);
+}
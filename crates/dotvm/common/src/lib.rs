@@ -15,5 +15,7 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 pub mod error;
+pub mod log_capture;
+pub mod telemetry;
 pub mod types;
 pub mod utils;
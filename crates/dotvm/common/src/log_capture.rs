@@ -0,0 +1,254 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Capture of tracing events for the centralized log store
+//!
+//! [`LogCaptureLayer`] turns every event that passes the subscriber's filter into a
+//! [`LogEvent`] and hands it to a bounded channel without blocking the caller. A shipper
+//! drains [`LogEvents`] into the store; when it falls behind, events are dropped and counted.
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Target of events emitted while shipping logs; these are never captured, so shipping
+/// failures cannot feed back into the store
+pub const LOG_SHIPPING_TARGET: &str = "dotvm::log_shipping";
+
+/// Event or span field naming the dot an event belongs to
+pub const DOT_ID_FIELD: &str = "dot_id";
+
+thread_local! {
+    static SUPPRESSED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// One captured tracing event
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogEvent {
+    /// Nanoseconds since the Unix epoch
+    pub timestamp_ns: u64,
+    /// `TRACE`, `DEBUG`, `INFO`, `WARN` or `ERROR`
+    pub level: String,
+    pub target: String,
+    pub message: String,
+    /// Service that emitted the event
+    pub service: String,
+    /// Dot the event belongs to, from the event itself or the closest span carrying one
+    pub dot_id: Option<String>,
+    /// Remaining event fields, formatted
+    pub fields: BTreeMap<String, String>,
+}
+
+/// Create a capture layer for `service` and the receiving end of its channel
+pub fn log_capture(service: &str, capacity: usize) -> (LogCaptureLayer, LogEvents) {
+    let (sender, receiver) = mpsc::channel(capacity.max(1));
+    let dropped = Arc::new(AtomicU64::new(0));
+    let layer = LogCaptureLayer {
+        service: service.to_string(),
+        sender,
+        dropped: dropped.clone(),
+    };
+    (layer, LogEvents { receiver, dropped })
+}
+
+/// Run `f` without capturing the events it emits on this thread
+///
+/// Shippers wrap their writes in this so the store's own logging is not shipped back into it.
+pub fn without_capture<R>(f: impl FnOnce() -> R) -> R {
+    let previous = SUPPRESSED.with(|suppressed| suppressed.replace(true));
+    let result = f();
+    SUPPRESSED.with(|suppressed| suppressed.set(previous));
+    result
+}
+
+/// Tracing layer sending captured events to a [`LogEvents`] receiver
+pub struct LogCaptureLayer {
+    service: String,
+    sender: mpsc::Sender<LogEvent>,
+    dropped: Arc<AtomicU64>,
+}
+
+/// Receiving end of a [`LogCaptureLayer`]
+pub struct LogEvents {
+    receiver: mpsc::Receiver<LogEvent>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl LogEvents {
+    pub async fn recv(&mut self) -> Option<LogEvent> {
+        self.receiver.recv().await
+    }
+
+    /// Wait for the next event outside of an async context
+    pub fn blocking_recv(&mut self) -> Option<LogEvent> {
+        self.receiver.blocking_recv()
+    }
+
+    /// The next event if one is already queued
+    pub fn try_recv(&mut self) -> Option<LogEvent> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Events dropped because the channel was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// `dot_id` recorded on a span, kept in its extensions
+struct SpanDotId(String);
+
+#[derive(Default)]
+struct FieldVisitor {
+    message: Option<String>,
+    fields: BTreeMap<String, String>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record(field, format!("{value:?}"));
+    }
+}
+
+impl FieldVisitor {
+    fn record(&mut self, field: &Field, value: String) {
+        if field.name() == "message" {
+            self.message = Some(value);
+        } else {
+            self.fields.insert(field.name().to_string(), value);
+        }
+    }
+}
+
+impl LogCaptureLayer {
+    fn remember_dot_id<S>(values: &Record<'_>, id: &Id, ctx: &Context<'_, S>)
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let mut visitor = FieldVisitor::default();
+        values.record(&mut visitor);
+        if let (Some(dot_id), Some(span)) = (visitor.fields.remove(DOT_ID_FIELD), ctx.span(id)) {
+            span.extensions_mut().replace(SpanDotId(dot_id));
+        }
+    }
+}
+
+impl<S> Layer<S> for LogCaptureLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        Self::remember_dot_id(&Record::new(attrs.values()), id, &ctx);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        Self::remember_dot_id(values, id, &ctx);
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if metadata.target().starts_with(LOG_SHIPPING_TARGET) || SUPPRESSED.with(Cell::get) {
+            return;
+        }
+
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let dot_id = visitor.fields.remove(DOT_ID_FIELD).or_else(|| {
+            ctx.event_scope(event)
+                .and_then(|mut scope| scope.find_map(|span| span.extensions().get::<SpanDotId>().map(|dot_id| dot_id.0.clone())))
+        });
+
+        let log_event = LogEvent {
+            timestamp_ns: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64,
+            level: metadata.level().to_string(),
+            target: metadata.target().to_string(),
+            message: visitor.message.unwrap_or_default(),
+            service: self.service.clone(),
+            dot_id,
+            fields: visitor.fields,
+        };
+        if self.sender.try_send(log_event).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    fn capture(capacity: usize, emit: impl FnOnce()) -> LogEvents {
+        let (layer, events) = log_capture("runtime", capacity);
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), emit);
+        events
+    }
+
+    #[test]
+    fn test_captures_fields_and_span_dot_id() {
+        let mut events = capture(16, || {
+            tracing::warn!(target: "dotvm::exec", attempts = 3, "retrying {}", "store");
+            let span = tracing::info_span!("execute", dot_id = "dot-1");
+            let _entered = span.enter();
+            tracing::info!(target: "dotvm::exec", "running");
+            tracing::info!(target: "dotvm::exec", dot_id = "dot-2", "explicit");
+        });
+
+        let first = events.try_recv().unwrap();
+        assert_eq!((first.level.as_str(), first.target.as_str(), first.message.as_str()), ("WARN", "dotvm::exec", "retrying store"));
+        assert_eq!(first.service, "runtime");
+        assert_eq!(first.dot_id, None);
+        assert_eq!(first.fields.get("attempts").map(String::as_str), Some("3"));
+        assert!(first.timestamp_ns > 0);
+
+        assert_eq!(events.try_recv().unwrap().dot_id.as_deref(), Some("dot-1"));
+        let explicit = events.try_recv().unwrap();
+        assert_eq!(explicit.dot_id.as_deref(), Some("dot-2"));
+        assert!(!explicit.fields.contains_key(DOT_ID_FIELD));
+        assert!(events.try_recv().is_none());
+    }
+
+    #[test]
+    fn test_skips_shipping_events_and_counts_drops() {
+        let mut events = capture(2, || {
+            tracing::warn!(target: LOG_SHIPPING_TARGET, "store unavailable");
+            without_capture(|| tracing::info!("written by the shipper"));
+            for i in 0..5 {
+                tracing::info!("event {i}");
+            }
+        });
+
+        assert_eq!(events.try_recv().unwrap().message, "event 0");
+        assert_eq!(events.try_recv().unwrap().message, "event 1");
+        assert!(events.try_recv().is_none());
+        assert_eq!(events.dropped(), 3);
+    }
+}
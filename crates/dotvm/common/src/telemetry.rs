@@ -0,0 +1,276 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Request tracing shared by the gateway and the runtime
+//!
+//! A W3C `traceparent` travels from the REST gateway through gRPC metadata into
+//! the runtime, so the spans each side records carry the same `trace_id`. When
+//! `OTEL_EXPORTER_OTLP_ENDPOINT` is set the spans are also exported over OTLP.
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use opentelemetry::trace::{SpanContext, SpanId, TraceContextExt, TraceError, TraceFlags, TraceId, TraceState};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::Resource;
+use thiserror::Error;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::{SubscriberInitExt, TryInitError};
+use tracing_subscriber::{EnvFilter, fmt as fmt_layer};
+
+use crate::log_capture::LogCaptureLayer;
+
+/// Header and gRPC metadata key carrying the trace context
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Header and gRPC metadata key carrying the request id
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// gRPC metadata key carrying the principal the gateway authenticated the request as
+pub const PRINCIPAL_HEADER: &str = "x-dotlanth-principal";
+
+/// gRPC metadata key carrying the roles of that principal, comma separated
+pub const ROLES_HEADER: &str = "x-dotlanth-roles";
+
+/// Environment variable holding the OTLP collector endpoint; spans are only exported when it is set
+pub const OTLP_ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+
+#[derive(Error, Debug)]
+pub enum TelemetryError {
+    #[error("Failed to install OTLP exporter: {0}")]
+    Exporter(#[from] TraceError),
+    #[error("Failed to install tracing subscriber: {0}")]
+    Subscriber(#[from] TryInitError),
+}
+
+/// W3C trace context of a span: the trace it belongs to and its own id
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: u128,
+    pub span_id: u64,
+    pub sampled: bool,
+}
+
+impl TraceContext {
+    /// Start a new trace
+    pub fn new_root() -> Self {
+        Self {
+            trace_id: (u128::from(random_id()) << 64) | u128::from(random_id()),
+            span_id: random_id(),
+            sampled: true,
+        }
+    }
+
+    /// Context of a new span in the same trace
+    pub fn child(&self) -> Self {
+        Self { span_id: random_id(), ..*self }
+    }
+
+    /// Parse a `traceparent` header, rejecting malformed or all-zero ids
+    pub fn parse(header: &str) -> Option<Self> {
+        let parts: Vec<&str> = header.trim().split('-').collect();
+        let [version, trace_id, span_id, flags, rest @ ..] = parts.as_slice() else {
+            return None;
+        };
+        // Version 00 has exactly four fields, later versions may append more
+        if !is_lower_hex(version, 2) || *version == "ff" || (*version == "00" && !rest.is_empty()) {
+            return None;
+        }
+        if !is_lower_hex(trace_id, 32) || !is_lower_hex(span_id, 16) || !is_lower_hex(flags, 2) {
+            return None;
+        }
+
+        let context = Self {
+            trace_id: u128::from_str_radix(trace_id, 16).ok()?,
+            span_id: u64::from_str_radix(span_id, 16).ok()?,
+            sampled: u8::from_str_radix(flags, 16).ok()? & 0x01 != 0,
+        };
+        (context.trace_id != 0 && context.span_id != 0).then_some(context)
+    }
+
+    /// Trace id as 32 lowercase hex digits, the form searched for in trace backends
+    pub fn trace_id_hex(&self) -> String {
+        format!("{:032x}", self.trace_id)
+    }
+
+    /// Span id as 16 lowercase hex digits
+    pub fn span_id_hex(&self) -> String {
+        format!("{:016x}", self.span_id)
+    }
+
+    /// The OpenTelemetry context of `span`, when spans are being exported
+    pub fn from_span(span: &Span) -> Option<Self> {
+        let context = span.context();
+        let otel_span = context.span();
+        let span_context = otel_span.span_context();
+        span_context.is_valid().then(|| Self {
+            trace_id: u128::from_be_bytes(span_context.trace_id().to_bytes()),
+            span_id: u64::from_be_bytes(span_context.span_id().to_bytes()),
+            sampled: span_context.is_sampled(),
+        })
+    }
+
+    /// Make this remote context the parent of `span` in exported traces
+    pub fn attach_as_parent(&self, span: &Span) {
+        let flags = if self.sampled { TraceFlags::SAMPLED } else { TraceFlags::default() };
+        let remote = SpanContext::new(
+            TraceId::from_bytes(self.trace_id.to_be_bytes()),
+            SpanId::from_bytes(self.span_id.to_be_bytes()),
+            flags,
+            true,
+            TraceState::default(),
+        );
+        span.set_parent(Context::new().with_remote_span_context(remote));
+    }
+}
+
+impl fmt::Display for TraceContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "00-{:032x}-{:016x}-{:02x}", self.trace_id, self.span_id, u8::from(self.sampled))
+    }
+}
+
+fn is_lower_hex(value: &str, len: usize) -> bool {
+    value.len() == len && value.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Non-zero id that is unique enough for tracing, without pulling in a random number generator
+fn random_id() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos());
+    hasher.finish().max(1)
+}
+
+/// Flushes exported spans when dropped; keep it alive for the life of the process
+#[must_use]
+pub struct TracingGuard {
+    exporting: bool,
+}
+
+impl TracingGuard {
+    /// Whether spans are being exported over OTLP
+    pub fn is_exporting(&self) -> bool {
+        self.exporting
+    }
+}
+
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        if self.exporting {
+            opentelemetry::global::shutdown_tracer_provider();
+        }
+    }
+}
+
+/// Install the global subscriber: formatted logs filtered by `RUST_LOG` (default `info`),
+/// plus an OTLP exporter when [`OTLP_ENDPOINT_ENV`] is set
+///
+/// Must be called from within a Tokio runtime, which the exporter uses to send batches.
+pub fn init_tracing(service_name: &str) -> Result<TracingGuard, TelemetryError> {
+    init_tracing_with_capture(service_name, None)
+}
+
+/// [`init_tracing`], also sending the events that pass the filter to `capture` for shipping
+/// to the centralized log store
+pub fn init_tracing_with_capture(service_name: &str, capture: Option<LogCaptureLayer>) -> Result<TracingGuard, TelemetryError> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let registry = tracing_subscriber::registry().with(filter).with(fmt_layer::layer()).with(capture);
+
+    let Some(endpoint) = std::env::var(OTLP_ENDPOINT_ENV).ok().filter(|endpoint| !endpoint.is_empty()) else {
+        registry.try_init()?;
+        return Ok(TracingGuard { exporting: false });
+    };
+
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint))
+        .with_trace_config(opentelemetry_sdk::trace::config().with_resource(Resource::new(vec![KeyValue::new("service.name", service_name.to_string())])))
+        .install_batch(opentelemetry_sdk::runtime::Tokio)?;
+    registry.with(tracing_opentelemetry::layer().with_tracer(tracer)).try_init()?;
+    Ok(TracingGuard { exporting: true })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traceparent_round_trip() {
+        let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let context = TraceContext::parse(header).unwrap();
+        assert_eq!(context.trace_id_hex(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(context.span_id_hex(), "00f067aa0ba902b7");
+        assert!(context.sampled);
+        assert_eq!(context.to_string(), header);
+
+        let child = context.child();
+        assert_eq!(child.trace_id, context.trace_id);
+        assert_ne!(child.span_id, context.span_id);
+    }
+
+    #[test]
+    fn test_rejects_malformed_traceparent() {
+        for header in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        ] {
+            assert_eq!(TraceContext::parse(header), None, "{header}");
+        }
+
+        // Later versions may carry extra fields
+        assert!(TraceContext::parse("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-extra").is_some_and(|c| !c.sampled));
+    }
+
+    #[test]
+    fn test_exported_spans_continue_the_remote_trace() {
+        use opentelemetry::trace::TracerProvider as _;
+
+        let provider = opentelemetry_sdk::trace::TracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let remote = TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").unwrap();
+
+        tracing::subscriber::with_default(subscriber, || {
+            assert_eq!(TraceContext::from_span(&Span::none()), None);
+
+            let span = tracing::info_span!("server");
+            remote.attach_as_parent(&span);
+            let exported = TraceContext::from_span(&span).unwrap();
+            assert_eq!(exported.trace_id, remote.trace_id);
+            assert_ne!(exported.span_id, remote.span_id);
+        });
+    }
+
+    #[test]
+    fn test_new_roots_are_distinct() {
+        let (a, b) = (TraceContext::new_root(), TraceContext::new_root());
+        assert_ne!(a.trace_id, b.trace_id);
+        assert_ne!(a.span_id, 0);
+        assert_eq!(TraceContext::parse(&a.to_string()), Some(a));
+    }
+}
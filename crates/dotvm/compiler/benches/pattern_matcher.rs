@@ -0,0 +1,73 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Pattern matcher benchmarks
+//!
+//! Compares the compiled pattern matcher with the per-pattern scanning matcher it
+//! replaced, on a pattern library and module resembling what dependency detection runs on.
+
+use criterion::{BenchmarkId, Criterion, Throughput, black_box, criterion_group, criterion_main};
+
+use dotvm_compiler::dependency_analysis::detection::pattern_matcher::PatternMatcher;
+
+#[allow(dead_code)]
+#[path = "../tests/support/pattern_baseline.rs"]
+mod pattern_baseline;
+
+use pattern_baseline::{ScanningMatcher, pattern_library, representative_module};
+
+/// Benchmark finding matches in modules of increasing size
+fn bench_find_matches(c: &mut Criterion) {
+    let patterns = pattern_library();
+    let mut group = c.benchmark_group("find_matches");
+
+    for functions in [10, 50, 200] {
+        let module = representative_module(functions);
+        group.throughput(Throughput::Bytes(module.len() as u64));
+
+        let mut compiled = PatternMatcher::new().with_max_matches(usize::MAX);
+        let mut scanning = ScanningMatcher::new(usize::MAX, false);
+        for pattern in &patterns {
+            compiled.add_pattern(pattern.clone());
+            scanning.add_pattern(pattern.clone());
+        }
+        compiled.compile();
+
+        group.bench_with_input(BenchmarkId::new("compiled", functions), &module, |b, module| b.iter(|| compiled.find_matches(black_box(module))));
+        group.bench_with_input(BenchmarkId::new("scanning", functions), &module, |b, module| b.iter(|| scanning.find_matches(black_box(module))));
+    }
+
+    group.finish();
+}
+
+/// Benchmark compiling the pattern library
+fn bench_compile(c: &mut Criterion) {
+    let patterns = pattern_library();
+
+    c.bench_function("compile_pattern_library", |b| {
+        b.iter(|| {
+            let mut matcher = PatternMatcher::new();
+            for pattern in &patterns {
+                matcher.add_pattern(pattern.clone());
+            }
+            matcher.compile();
+            matcher
+        })
+    });
+}
+
+criterion_group!(benches, bench_find_matches, bench_compile);
+criterion_main!(benches);
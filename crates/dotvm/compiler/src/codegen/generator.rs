@@ -293,9 +293,12 @@ mod tests {
                 page_size: 65536,
                 segments: Vec::new(),
                 protection: crate::transpiler::types::variables::MemoryProtection::default(),
+                regions: Vec::new(),
             },
             exports: vec![],
             imports: vec![],
+            element_segments: vec![],
+            start_function: None,
             metadata: crate::transpiler::types::module::ModuleMetadata::default(),
         };
 
@@ -147,6 +147,7 @@ mod tests {
                 page_size: 65536,
                 segments: Vec::new(),
                 protection: crate::transpiler::types::variables::MemoryProtection::default(),
+                regions: Vec::new(),
             },
             exports: vec![ExportInfo {
                 name: "main".to_string(),
@@ -162,6 +163,8 @@ mod tests {
                 description: None,
                 is_required: true,
             }],
+            element_segments: vec![],
+            start_function: None,
             metadata: crate::transpiler::types::module::ModuleMetadata::default(),
         };
 
@@ -15,61 +15,163 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 //! Dominance analysis (builds dominator tree)
+//!
+//! Immediate dominators are computed with the iterative algorithm of Cooper,
+//! Harvey and Kennedy: blocks are visited in reverse postorder and each block's
+//! dominator is the nearest common ancestor of its processed predecessors in the
+//! tree built so far. On reducible graphs this converges in two passes, so the
+//! cost is close to linear in the number of edges.
 
-use super::{ControlFlowGraph, ControlFlowNode};
+use super::ControlFlowGraph;
 use std::collections::{HashMap, HashSet};
 
+/// Dominator tree of the blocks reachable from the entry
+#[derive(Debug, Clone, Default)]
+pub struct DominatorTree {
+    /// Reachable blocks in reverse postorder of a depth-first search
+    rpo: Vec<usize>,
+    rpo_index: HashMap<usize, usize>,
+    /// Immediate dominator of every reachable block but the entry
+    idoms: HashMap<usize, usize>,
+    /// Preorder entry and exit numbers in the tree, for constant-time dominance queries
+    intervals: HashMap<usize, (usize, usize)>,
+}
+
+impl DominatorTree {
+    /// Reachable blocks in reverse postorder
+    pub fn reverse_postorder(&self) -> &[usize] {
+        &self.rpo
+    }
+
+    /// Position of the block in reverse postorder, `None` if it is unreachable
+    pub fn rpo_index(&self, node: usize) -> Option<usize> {
+        self.rpo_index.get(&node).copied()
+    }
+
+    /// Whether the block is reachable from the entry
+    pub fn contains(&self, node: usize) -> bool {
+        self.rpo_index.contains_key(&node)
+    }
+
+    /// Immediate dominator of the block; `None` for the entry and unreachable blocks
+    pub fn idom(&self, node: usize) -> Option<usize> {
+        self.idoms.get(&node).copied()
+    }
+
+    /// Immediate dominators of every reachable block but the entry
+    pub fn idoms(&self) -> &HashMap<usize, usize> {
+        &self.idoms
+    }
+
+    /// Whether every path from the entry to `node` passes through `dominator`
+    ///
+    /// A block dominates itself; unreachable blocks neither dominate nor are dominated.
+    pub fn dominates(&self, dominator: usize, node: usize) -> bool {
+        match (self.intervals.get(&dominator), self.intervals.get(&node)) {
+            (Some(&(enter, exit)), Some(&(node_enter, _))) => enter <= node_enter && node_enter < exit,
+            _ => false,
+        }
+    }
+}
+
 /// Computes immediate dominators for each node in the CFG
 pub struct DominanceAnalyzer;
 
 impl DominanceAnalyzer {
-    /// Returns a map from node ID to its immediate dominator
-    pub fn compute_idoms(cfg: &ControlFlowGraph) -> HashMap<usize, usize> {
-        let mut idom: HashMap<usize, usize> = HashMap::new();
-        let nodes: Vec<usize> = cfg.nodes.keys().copied().collect();
-        if nodes.is_empty() {
-            return idom;
-        }
-        let entry = cfg.entry_node;
-        // Initialize dom sets: dom(entry) = {entry}, others = all nodes
-        let mut doms: HashMap<usize, HashSet<usize>> = nodes.iter().map(|&n| if n == entry { (n, [entry].into()) } else { (n, nodes.iter().copied().collect()) }).collect();
+    /// Build the dominator tree of the blocks reachable from the entry
+    pub fn compute(cfg: &ControlFlowGraph) -> DominatorTree {
+        let rpo = reverse_postorder(cfg);
+        let rpo_index: HashMap<usize, usize> = rpo.iter().enumerate().map(|(i, &n)| (n, i)).collect();
+        let preds: Vec<Vec<usize>> = rpo.iter().map(|&n| cfg.predecessors(n).iter().filter_map(|p| rpo_index.get(p).copied()).collect()).collect();
 
+        // Indices into `rpo`; the entry is its own dominator while iterating
+        let mut idom: Vec<Option<usize>> = vec![None; rpo.len()];
+        if !rpo.is_empty() {
+            idom[0] = Some(0);
+        }
         let mut changed = true;
         while changed {
             changed = false;
-            for &n in &nodes {
-                if n == entry {
+            for node in 1..rpo.len() {
+                let mut processed = preds[node].iter().copied().filter(|&p| idom[p].is_some());
+                let Some(first) = processed.next() else {
                     continue;
-                }
-                // intersect preds' dom sets
-                let preds: Vec<usize> = cfg.edges.iter().filter_map(|e| if e.to == n { Some(e.from) } else { None }).collect();
-                if preds.is_empty() {
-                    continue;
-                }
-                let mut new_dom = preds
-                    .iter()
-                    .map(|&p| doms.get(&p).unwrap().clone())
-                    .fold(None, |acc: Option<HashSet<usize>>, s| Some(if let Some(a) = acc { &a & &s } else { s }))
-                    .unwrap();
-                new_dom.insert(n);
-                if new_dom != doms[&n] {
-                    doms.insert(n, new_dom);
+                };
+                let new_idom = processed.fold(first, |a, b| intersect(&idom, a, b));
+                if idom[node] != Some(new_idom) {
+                    idom[node] = Some(new_idom);
                     changed = true;
                 }
             }
         }
 
-        // extract idoms: for each n != entry, idom(n) = immediate dominator
-        for &n in &nodes {
-            if n == entry {
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); rpo.len()];
+        for (node, parent) in idom.iter().enumerate().skip(1) {
+            if let Some(parent) = parent {
+                children[*parent].push(node);
+            }
+        }
+        let mut intervals = HashMap::with_capacity(rpo.len());
+        let mut counter = 0;
+        let mut stack = if rpo.is_empty() { Vec::new() } else { vec![(0, false)] };
+        while let Some((node, done)) = stack.pop() {
+            if done {
+                if let Some((_, exit)) = intervals.get_mut(&rpo[node]) {
+                    *exit = counter;
+                }
                 continue;
             }
-            let ds = &doms[&n];
-            // pick maximal dom in ds except n
-            let idominator = ds.iter().filter(|&&d| d != n).copied().max().unwrap();
-            idom.insert(n, idominator);
+            intervals.insert(rpo[node], (counter, counter));
+            counter += 1;
+            stack.push((node, true));
+            stack.extend(children[node].iter().map(|&child| (child, false)));
+        }
+
+        let idoms = idom.iter().enumerate().skip(1).filter_map(|(node, parent)| parent.map(|p| (rpo[node], rpo[p]))).collect();
+        DominatorTree { rpo, rpo_index, idoms, intervals }
+    }
+
+    /// Returns a map from node ID to its immediate dominator
+    pub fn compute_idoms(cfg: &ControlFlowGraph) -> HashMap<usize, usize> {
+        Self::compute(cfg).idoms
+    }
+}
+
+/// Nearest common dominator of two blocks given by their reverse postorder index
+fn intersect(idom: &[Option<usize>], mut a: usize, mut b: usize) -> usize {
+    while a != b {
+        while a > b {
+            a = idom[a].expect("processed blocks have a dominator");
+        }
+        while b > a {
+            b = idom[b].expect("processed blocks have a dominator");
         }
+    }
+    a
+}
 
-        idom
+/// Reachable blocks in reverse postorder, without recursion so deep graphs cannot
+/// overflow the stack
+fn reverse_postorder(cfg: &ControlFlowGraph) -> Vec<usize> {
+    if !cfg.nodes.contains_key(&cfg.entry_node) {
+        return Vec::new();
+    }
+    let mut visited = HashSet::from([cfg.entry_node]);
+    let mut postorder = Vec::with_capacity(cfg.nodes.len());
+    // Each frame is a block and the position of the next successor to visit
+    let mut stack = vec![(cfg.entry_node, 0)];
+    while let Some((node, next)) = stack.last_mut() {
+        let node = *node;
+        if let Some(&succ) = cfg.successors(node).get(*next) {
+            *next += 1;
+            if visited.insert(succ) {
+                stack.push((succ, 0));
+            }
+        } else {
+            postorder.push(node);
+            stack.pop();
+        }
     }
+    postorder.reverse();
+    postorder
 }
@@ -16,10 +16,12 @@
 
 //! Constructs the control flow graph (basic blocks and edges)
 
-use super::{ControlFlowEdge, ControlFlowEdgeType, ControlFlowGraph, ControlFlowNode};
+use super::{ControlFlowEdge, ControlFlowEdgeType, ControlFlowGraph, ControlFlowNode, Terminator};
+use crate::dependency_analysis::analyzers::{AnalysisError, AnalysisResult};
 use std::collections::HashMap;
 
 /// Builds a control flow graph from a sequence of basic blocks
+#[derive(Default)]
 pub struct ControlFlowGraphBuilder;
 
 impl ControlFlowGraphBuilder {
@@ -28,40 +30,67 @@ impl ControlFlowGraphBuilder {
         Self
     }
 
-    /// Create the CFG by linking nodes sequentially & by terminator logic
-    pub fn build(mut nodes: Vec<ControlFlowNode>) -> ControlFlowGraph {
-        let mut edges = Vec::new();
-        let mut node_map: HashMap<usize, ControlFlowNode> = nodes.into_iter().map(|n| (n.id, n)).collect();
-
-        // Sequential edges between consecutive blocks
-        let mut ids: Vec<usize> = node_map.keys().copied().collect();
+    /// Create the CFG by linking nodes by their terminators, then analyse it
+    ///
+    /// The block with the lowest ID is the entry. Fails if there are no blocks, two
+    /// blocks share an ID or a terminator names a block that does not exist.
+    pub fn build(nodes: Vec<ControlFlowNode>) -> AnalysisResult<ControlFlowGraph> {
+        let mut ids: Vec<usize> = nodes.iter().map(|n| n.id).collect();
         ids.sort_unstable();
-        for window in ids.windows(2) {
-            let from = window[0];
-            let to = window[1];
-            edges.push(ControlFlowEdge {
-                from,
-                to,
-                edge_type: ControlFlowEdgeType::Sequential,
-                condition: None,
-            });
+        let Some(&entry_node) = ids.first() else {
+            return Err(AnalysisError::EmptyInput);
+        };
+        if let Some(pair) = ids.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(AnalysisError::InvalidInput(format!("duplicate basic block {}", pair[0])));
         }
+        let node_map: HashMap<usize, ControlFlowNode> = nodes.into_iter().map(|n| (n.id, n)).collect();
 
-        // TODO: add conditional and loop edges based on node metadata
+        let mut edges = Vec::new();
+        for (position, &from) in ids.iter().enumerate() {
+            let next = ids.get(position + 1).copied();
+            let edge = |to: usize, edge_type: ControlFlowEdgeType, condition: Option<String>| ControlFlowEdge { from, to, edge_type, condition };
+            match &node_map[&from].terminator {
+                Terminator::FallThrough => edges.extend(next.map(|to| edge(to, ControlFlowEdgeType::Sequential, None))),
+                Terminator::Jump(to) => edges.push(edge(*to, ControlFlowEdgeType::Unconditional, None)),
+                Terminator::Branch { target, condition } => {
+                    edges.push(edge(*target, ControlFlowEdgeType::Conditional, condition.clone()));
+                    edges.extend(next.map(|to| edge(to, ControlFlowEdgeType::Sequential, None)));
+                }
+                Terminator::Switch(targets) => edges.extend(targets.iter().map(|&to| edge(to, ControlFlowEdgeType::Conditional, None))),
+                Terminator::Return => {}
+            }
+        }
+        if let Some(dangling) = edges.iter().find(|e| !node_map.contains_key(&e.to)) {
+            return Err(AnalysisError::InvalidInput(format!("basic block {} jumps to missing block {}", dangling.from, dangling.to)));
+        }
 
-        // Build entry/exit markers
-        let entry_node = *ids.first().unwrap();
-        let exit_nodes: Vec<usize> = ids.iter().copied().rev().take(1).collect();
+        let mut successors: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut predecessors: HashMap<usize, Vec<usize>> = HashMap::new();
+        for e in &edges {
+            let succs = successors.entry(e.from).or_default();
+            // A branch whose target is also the next block is one successor
+            if !succs.contains(&e.to) {
+                succs.push(e.to);
+                predecessors.entry(e.to).or_default().push(e.from);
+            }
+        }
 
-        ControlFlowGraph {
+        let mut cfg = ControlFlowGraph {
             nodes: node_map,
             edges,
             entry_node,
-            exit_node: exit_nodes.first().copied(),
-            exit_nodes,
+            exit_node: None,
+            exit_nodes: Vec::new(),
             loops: Vec::new(),
+            irreducible_regions: Vec::new(),
             unreachable_blocks: Vec::new(),
+            dominators: Default::default(),
             complexity: Default::default(),
-        }
+            successors,
+            predecessors,
+            loop_depths: HashMap::new(),
+        };
+        cfg.analyze();
+        Ok(cfg)
     }
 }
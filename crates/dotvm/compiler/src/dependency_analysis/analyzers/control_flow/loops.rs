@@ -15,41 +15,207 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 //! Loop detection in control flow graphs
+//!
+//! An edge is retreating when it leads to a block at or before its source in
+//! reverse postorder, closing a cycle. A retreating edge whose target dominates its
+//! source is a back edge, and the target heads a natural loop made of every block
+//! that reaches the back edge without passing through the header; back edges to
+//! the same header make up one loop. Any other retreating edge closes a cycle with
+//! several entries, which is reported as an irreducible region instead: the graph
+//! is reducible exactly when removing its back edges leaves it acyclic, so the
+//! regions are the cycles that remain after removing them.
 
-use super::{ControlFlowEdge, ControlFlowGraph, ControlFlowLoop, LoopType};
+use super::{ControlFlowGraph, ControlFlowLoop, IrreducibleRegion, LoopType};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Loops and irreducible regions of a graph
+#[derive(Debug, Clone, Default)]
+pub struct LoopForest {
+    /// Natural loops, outer loops before the loops they contain
+    pub loops: Vec<ControlFlowLoop>,
+    pub irreducible_regions: Vec<IrreducibleRegion>,
+    /// Number of loops containing each block that is in a loop
+    pub depths: HashMap<usize, usize>,
+}
 
 /// Detects loops by finding back edges and their bodies
 pub struct LoopDetector;
 
 impl LoopDetector {
-    /// Returns discovered loops (header, body nodes, back edges, type)
-    pub fn detect(cfg: &ControlFlowGraph) -> Vec<ControlFlowLoop> {
-        let mut loops = Vec::new();
-        // A simple heuristic: any edge from a later node to an earlier is a back edge
-        for edge in &cfg.edges {
-            if edge.from > edge.to {
-                // collect nodes in the loop
-                let mut body = Vec::new();
-                // BFS from header
-                let mut stack = vec![edge.from];
-                while let Some(n) = stack.pop() {
-                    if !body.contains(&n) {
-                        body.push(n);
-                        for succ_edge in &cfg.edges {
-                            if succ_edge.from == n {
-                                stack.push(succ_edge.to);
-                            }
-                        }
+    /// Find the natural loops and irreducible regions of the graph
+    ///
+    /// Needs the graph's dominator tree to have been computed.
+    pub fn detect(cfg: &ControlFlowGraph) -> LoopForest {
+        let dominators = &cfg.dominators;
+        let mut latches: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        let mut retreating = Vec::new();
+        for (index, &node) in dominators.reverse_postorder().iter().enumerate() {
+            for &succ in cfg.successors(node) {
+                if dominators.rpo_index(succ).is_some_and(|succ_index| succ_index <= index) {
+                    if dominators.dominates(succ, node) {
+                        latches.entry(succ).or_default().push(node);
+                    } else {
+                        retreating.push((node, succ));
+                    }
+                }
+            }
+        }
+
+        let mut loops: Vec<ControlFlowLoop> = latches.into_iter().map(|(header, latches)| natural_loop(cfg, header, latches)).collect();
+        // A loop's body is larger than that of every loop nested in it
+        loops.sort_by_key(|l| (std::cmp::Reverse(l.body.len()), dominators.rpo_index(l.header)));
+
+        let mut depths: HashMap<usize, usize> = HashMap::new();
+        let mut innermost: HashMap<usize, usize> = HashMap::new();
+        for l in &mut loops {
+            l.parent = innermost.get(&l.header).copied();
+            for &node in &l.body {
+                *depths.entry(node).or_default() += 1;
+                innermost.insert(node, l.header);
+            }
+            l.depth = depths[&l.header];
+        }
+
+        let irreducible_regions = if retreating.is_empty() { Vec::new() } else { irreducible_regions(cfg, &loops, retreating) };
+        LoopForest { loops, irreducible_regions, depths }
+    }
+}
+
+/// The loop of a header and the sources of its back edges
+fn natural_loop(cfg: &ControlFlowGraph, header: usize, latches: Vec<usize>) -> ControlFlowLoop {
+    let mut body = HashSet::from([header]);
+    let mut stack: Vec<usize> = latches.iter().copied().filter(|&latch| body.insert(latch)).collect();
+    while let Some(node) = stack.pop() {
+        for &pred in cfg.predecessors(node) {
+            if cfg.dominators.contains(pred) && body.insert(pred) {
+                stack.push(pred);
+            }
+        }
+    }
+
+    let mut sorted: Vec<usize> = body.iter().copied().collect();
+    sorted.sort_unstable();
+    let exits: Vec<(usize, usize)> = sorted
+        .iter()
+        .flat_map(|&node| cfg.successors(node).iter().filter(|succ| !body.contains(succ)).map(move |&succ| (node, succ)))
+        .collect();
+
+    let leaves = |node: usize| exits.iter().any(|&(from, _)| from == node);
+    let loop_type = if exits.is_empty() {
+        LoopType::Infinite
+    } else if leaves(header) {
+        LoopType::While
+    } else if latches.iter().any(|&latch| leaves(latch)) {
+        LoopType::DoWhile
+    } else {
+        LoopType::Unstructured
+    };
+
+    ControlFlowLoop {
+        header,
+        body: sorted,
+        back_edges: latches.into_iter().map(|latch| (latch, header)).collect(),
+        exits,
+        parent: None,
+        depth: 0,
+        loop_type,
+    }
+}
+
+/// Cycles left once back edges are removed, each holding some of the retreating edges
+fn irreducible_regions(cfg: &ControlFlowGraph, loops: &[ControlFlowLoop], retreating: Vec<(usize, usize)>) -> Vec<IrreducibleRegion> {
+    let dominators = &cfg.dominators;
+    let rpo = dominators.reverse_postorder();
+    let back_edges: HashSet<(usize, usize)> = loops.iter().flat_map(|l| l.back_edges.iter().copied()).collect();
+    let forward: Vec<Vec<usize>> = rpo
+        .iter()
+        .map(|&node| {
+            cfg.successors(node)
+                .iter()
+                .filter(|&&succ| !back_edges.contains(&(node, succ)))
+                .filter_map(|&succ| dominators.rpo_index(succ))
+                .collect()
+        })
+        .collect();
+    let component = strongly_connected_components(&forward);
+    let component_of = |node: usize| dominators.rpo_index(node).map(|index| component[index]);
+
+    // Both ends of a retreating edge are in the cycle it closes
+    let mut regions: BTreeMap<usize, Vec<(usize, usize)>> = BTreeMap::new();
+    for edge in retreating {
+        if let Some(c) = component_of(edge.0) {
+            regions.entry(c).or_default().push(edge);
+        }
+    }
+    regions
+        .into_iter()
+        .map(|(c, retreating_edges)| {
+            let mut nodes: Vec<usize> = rpo.iter().copied().filter(|&node| component_of(node) == Some(c)).collect();
+            nodes.sort_unstable();
+            let entries = nodes
+                .iter()
+                .copied()
+                .filter(|&node| node == cfg.entry_node || cfg.predecessors(node).iter().any(|&pred| component_of(pred).is_some_and(|pc| pc != c)))
+                .collect();
+            IrreducibleRegion { nodes, entries, retreating_edges }
+        })
+        .collect()
+}
+
+/// Component of every node, by Tarjan's algorithm with an explicit stack
+fn strongly_connected_components(successors: &[Vec<usize>]) -> Vec<usize> {
+    const UNVISITED: usize = usize::MAX;
+    let n = successors.len();
+    let mut index = vec![UNVISITED; n];
+    let mut low = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut component = vec![UNVISITED; n];
+    let mut stack = Vec::new();
+    let mut next_index = 0;
+    let mut components = 0;
+
+    for root in 0..n {
+        if index[root] != UNVISITED {
+            continue;
+        }
+        let mut frames = vec![(root, 0)];
+        index[root] = next_index;
+        low[root] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root] = true;
+
+        while let Some(&(node, position)) = frames.last() {
+            if let Some(&succ) = successors[node].get(position) {
+                frames.last_mut().unwrap().1 += 1;
+                if index[succ] == UNVISITED {
+                    index[succ] = next_index;
+                    low[succ] = next_index;
+                    next_index += 1;
+                    stack.push(succ);
+                    on_stack[succ] = true;
+                    frames.push((succ, 0));
+                } else if on_stack[succ] {
+                    low[node] = low[node].min(index[succ]);
+                }
+                continue;
+            }
+
+            frames.pop();
+            if let Some(&(parent, _)) = frames.last() {
+                low[parent] = low[parent].min(low[node]);
+            }
+            if low[node] == index[node] {
+                while let Some(member) = stack.pop() {
+                    on_stack[member] = false;
+                    component[member] = components;
+                    if member == node {
+                        break;
                     }
                 }
-                loops.push(ControlFlowLoop {
-                    header: edge.to,
-                    body: body.into_iter().collect(),
-                    back_edges: vec![(edge.from, edge.to)],
-                    loop_type: LoopType::While,
-                });
+                components += 1;
             }
         }
-        loops
     }
+    component
 }
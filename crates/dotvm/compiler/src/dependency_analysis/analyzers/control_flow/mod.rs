@@ -0,0 +1,363 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Control flow graph construction and analysis
+//!
+//! [`ControlFlowGraphBuilder`] links basic blocks by their terminators and analyses
+//! the graph once: reachability, the dominator tree, natural loops with their
+//! nesting depth, and cyclomatic complexity. A loop is only reported for back
+//! edges, edges whose target dominates their source. A cycle that can be entered
+//! at more than one block has no such edge; it is reported as an
+//! [`IrreducibleRegion`] rather than as a loop whose header would be arbitrary.
+
+pub mod dominance;
+pub mod graph_builder;
+pub mod loops;
+pub mod reachability;
+
+pub use dominance::{DominanceAnalyzer, DominatorTree};
+pub use graph_builder::ControlFlowGraphBuilder;
+pub use loops::{LoopDetector, LoopForest};
+pub use reachability::ReachabilityAnalyzer;
+
+use std::collections::HashMap;
+
+/// How a basic block hands control on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Terminator {
+    /// Continues with the block with the next higher ID
+    FallThrough,
+    /// Always jumps to the block
+    Jump(usize),
+    /// Jumps to `target` if the condition holds, otherwise falls through
+    Branch { target: usize, condition: Option<String> },
+    /// Jumps to one of several blocks, as a `br_table` does
+    Switch(Vec<usize>),
+    /// Leaves the function
+    Return,
+}
+
+/// A basic block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlFlowNode {
+    pub id: usize,
+    pub terminator: Terminator,
+}
+
+impl ControlFlowNode {
+    /// Create a block ending in the given terminator
+    pub fn new(id: usize, terminator: Terminator) -> Self {
+        Self { id, terminator }
+    }
+}
+
+/// Kind of a control flow edge
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlFlowEdgeType {
+    /// Fall-through into the next block
+    Sequential,
+    /// Unconditional jump
+    Unconditional,
+    /// Taken branch of a conditional or switch
+    Conditional,
+}
+
+/// An edge between two basic blocks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlFlowEdge {
+    pub from: usize,
+    pub to: usize,
+    pub edge_type: ControlFlowEdgeType,
+    pub condition: Option<String>,
+}
+
+/// Where a loop tests whether to leave
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopType {
+    /// The header can leave the loop
+    While,
+    /// Only a block jumping back to the header can leave the loop
+    DoWhile,
+    /// Only blocks in the middle of the body can leave the loop, as with `break`
+    Unstructured,
+    /// Nothing leaves the loop
+    Infinite,
+}
+
+/// A natural loop: a header and every block that reaches one of its back edges
+/// without passing through the header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlFlowLoop {
+    pub header: usize,
+    /// Blocks of the loop including the header and nested loops, sorted by ID
+    pub body: Vec<usize>,
+    /// Edges jumping back to the header
+    pub back_edges: Vec<(usize, usize)>,
+    /// Edges from a block of the loop to a block outside it
+    pub exits: Vec<(usize, usize)>,
+    /// Header of the innermost loop containing this one
+    pub parent: Option<usize>,
+    /// Nesting depth; outermost loops have depth 1
+    pub depth: usize,
+    pub loop_type: LoopType,
+}
+
+impl ControlFlowLoop {
+    /// Whether the block belongs to the loop
+    pub fn contains(&self, node: usize) -> bool {
+        self.body.binary_search(&node).is_ok()
+    }
+}
+
+/// A cycle with more than one entry block, which no natural loop describes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IrreducibleRegion {
+    /// Blocks of the cycle, sorted by ID
+    pub nodes: Vec<usize>,
+    /// Blocks of the cycle that control can enter it at
+    pub entries: Vec<usize>,
+    /// Edges closing the cycle whose target does not dominate their source
+    pub retreating_edges: Vec<(usize, usize)>,
+}
+
+/// Complexity metrics of the reachable part of a graph
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ControlFlowComplexity {
+    /// Edges minus blocks plus two
+    pub cyclomatic: usize,
+    pub loop_count: usize,
+    pub max_loop_depth: usize,
+}
+
+/// Control flow graph of a function with the results of analysing it
+#[derive(Debug, Clone)]
+pub struct ControlFlowGraph {
+    pub nodes: HashMap<usize, ControlFlowNode>,
+    pub edges: Vec<ControlFlowEdge>,
+    pub entry_node: usize,
+    /// The exit block when there is exactly one
+    pub exit_node: Option<usize>,
+    /// Reachable blocks without successors
+    pub exit_nodes: Vec<usize>,
+    /// Natural loops, outer loops before the loops they contain
+    pub loops: Vec<ControlFlowLoop>,
+    pub irreducible_regions: Vec<IrreducibleRegion>,
+    pub unreachable_blocks: Vec<usize>,
+    pub dominators: DominatorTree,
+    pub complexity: ControlFlowComplexity,
+    successors: HashMap<usize, Vec<usize>>,
+    predecessors: HashMap<usize, Vec<usize>>,
+    loop_depths: HashMap<usize, usize>,
+}
+
+impl ControlFlowGraph {
+    /// Blocks the block has an edge to
+    pub fn successors(&self, node: usize) -> &[usize] {
+        self.successors.get(&node).map_or(&[], Vec::as_slice)
+    }
+
+    /// Blocks with an edge to the block
+    pub fn predecessors(&self, node: usize) -> &[usize] {
+        self.predecessors.get(&node).map_or(&[], Vec::as_slice)
+    }
+
+    /// Natural loops, outer loops before the loops they contain
+    pub fn loops(&self) -> &[ControlFlowLoop] {
+        &self.loops
+    }
+
+    /// Innermost loop containing the block
+    pub fn innermost_loop(&self, node: usize) -> Option<&ControlFlowLoop> {
+        self.loops.iter().rev().find(|l| l.contains(node))
+    }
+
+    /// Number of loops containing the block; 0 outside any loop
+    ///
+    /// Only natural loops count, so blocks that are in an irreducible region but
+    /// no natural loop have depth 0.
+    pub fn loop_depth(&self, node: usize) -> usize {
+        self.loop_depths.get(&node).copied().unwrap_or(0)
+    }
+
+    /// Whether every cycle is a natural loop
+    pub fn is_reducible(&self) -> bool {
+        self.irreducible_regions.is_empty()
+    }
+
+    /// Whether every path from the entry to `node` passes through `dominator`
+    pub fn dominates(&self, dominator: usize, node: usize) -> bool {
+        self.dominators.dominates(dominator, node)
+    }
+
+    /// Compute the analyses from the nodes and edges
+    fn analyze(&mut self) {
+        self.unreachable_blocks = ReachabilityAnalyzer::find_unreachable(self);
+        self.dominators = DominanceAnalyzer::compute(self);
+        let detected = LoopDetector::detect(self);
+        self.loops = detected.loops;
+        self.irreducible_regions = detected.irreducible_regions;
+        self.loop_depths = detected.depths;
+
+        self.exit_nodes = self.dominators.reverse_postorder().iter().copied().filter(|&n| self.successors(n).is_empty()).collect();
+        self.exit_nodes.sort_unstable();
+        self.exit_node = match self.exit_nodes.as_slice() {
+            [exit] => Some(*exit),
+            _ => None,
+        };
+
+        let reachable_edges = self.edges.iter().filter(|e| self.dominators.contains(e.from)).count();
+        let reachable_nodes = self.dominators.reverse_postorder().len();
+        self.complexity = ControlFlowComplexity {
+            cyclomatic: (reachable_edges + 2).saturating_sub(reachable_nodes),
+            loop_count: self.loops.len(),
+            max_loop_depth: self.loops.iter().map(|l| l.depth).max().unwrap_or(0),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(terminators: Vec<Terminator>) -> ControlFlowGraph {
+        ControlFlowGraphBuilder::build(terminators.into_iter().enumerate().map(|(id, t)| ControlFlowNode::new(id, t)).collect()).unwrap()
+    }
+
+    fn branch(target: usize) -> Terminator {
+        Terminator::Branch { target, condition: None }
+    }
+
+    #[test]
+    fn test_nested_loops() {
+        // 1 is a while loop around the do-while loop 2..3; 5 is dead code
+        let cfg = build(vec![
+            Terminator::FallThrough,
+            branch(6),
+            Terminator::FallThrough,
+            branch(2),
+            Terminator::Jump(1),
+            Terminator::Return,
+            Terminator::Return,
+        ]);
+
+        assert_eq!(cfg.loops().len(), 2);
+        let outer = &cfg.loops()[0];
+        assert_eq!((outer.header, outer.body.as_slice(), outer.exits.as_slice()), (1, [1, 2, 3, 4].as_slice(), [(1, 6)].as_slice()));
+        assert_eq!((outer.loop_type, outer.depth, outer.parent), (LoopType::While, 1, None));
+        let inner = &cfg.loops()[1];
+        assert_eq!((inner.header, inner.body.as_slice(), inner.back_edges.as_slice()), (2, [2, 3].as_slice(), [(3, 2)].as_slice()));
+        assert_eq!((inner.loop_type, inner.depth, inner.parent), (LoopType::DoWhile, 2, Some(1)));
+
+        assert_eq!([0, 1, 2, 3, 4, 6].map(|n| cfg.loop_depth(n)), [0, 1, 2, 2, 1, 0]);
+        assert_eq!(cfg.innermost_loop(3).map(|l| l.header), Some(2));
+        assert_eq!(cfg.unreachable_blocks, [5]);
+        assert_eq!(cfg.exit_node, Some(6));
+        assert!(cfg.is_reducible());
+        assert_eq!(
+            cfg.complexity,
+            ControlFlowComplexity {
+                cyclomatic: 3,
+                loop_count: 2,
+                max_loop_depth: 2
+            }
+        );
+
+        assert_eq!((cfg.dominators.idom(2), cfg.dominators.idom(6), cfg.dominators.idom(0)), (Some(1), Some(1), None));
+        assert!(cfg.dominates(1, 4) && cfg.dominates(2, 4) && cfg.dominates(4, 4) && !cfg.dominates(2, 6) && !cfg.dominates(5, 6));
+    }
+
+    #[test]
+    fn test_loop_with_multiple_exits() {
+        // The loop 1..3 can be left from each of its blocks
+        let cfg = build(vec![
+            Terminator::FallThrough,
+            branch(5),
+            branch(6),
+            branch(1),
+            Terminator::Return,
+            Terminator::Return,
+            Terminator::Return,
+        ]);
+
+        let [l] = cfg.loops() else { panic!("expected one loop: {:?}", cfg.loops()) };
+        assert_eq!(l.body, [1, 2, 3]);
+        assert_eq!(l.exits, [(1, 5), (2, 6), (3, 4)]);
+        assert_eq!(l.loop_type, LoopType::While);
+        assert_eq!((cfg.exit_nodes.as_slice(), cfg.exit_node), ([4, 5, 6].as_slice(), None));
+
+        // Left only through a break in the middle, or not at all
+        let cfg = build(vec![Terminator::FallThrough, branch(4), Terminator::Jump(0), Terminator::Return, Terminator::Return]);
+        assert_eq!(cfg.loops()[0].loop_type, LoopType::Unstructured);
+        let cfg = build(vec![Terminator::FallThrough, Terminator::Jump(0)]);
+        assert_eq!((cfg.loops()[0].loop_type, cfg.loops()[0].exits.len()), (LoopType::Infinite, 0));
+    }
+
+    #[test]
+    fn test_irreducible_cycle_is_reported_not_looped() {
+        // 1 and 2 form a cycle that 0 can enter at either block
+        let cfg = build(vec![branch(2), Terminator::FallThrough, Terminator::Jump(1)]);
+        assert!(cfg.loops().is_empty());
+        assert!(!cfg.is_reducible());
+        let [region] = cfg.irreducible_regions.as_slice() else { panic!("expected one region") };
+        assert_eq!((region.nodes.as_slice(), region.entries.as_slice()), ([1, 2].as_slice(), [1, 2].as_slice()));
+        assert_eq!(region.retreating_edges.len(), 1);
+        assert_eq!(cfg.loop_depth(1), 0);
+
+        // Inside a natural loop, the enclosing loop is still found and the region excludes it
+        let cfg = build(vec![Terminator::FallThrough, branch(3), Terminator::FallThrough, branch(2), branch(1), Terminator::Return]);
+        let [outer] = cfg.loops() else { panic!("expected one loop: {:?}", cfg.loops()) };
+        assert_eq!((outer.header, outer.body.as_slice(), outer.loop_type), (1, [1, 2, 3, 4].as_slice(), LoopType::DoWhile));
+        let [region] = cfg.irreducible_regions.as_slice() else { panic!("expected one region") };
+        assert_eq!((region.nodes.as_slice(), region.entries.as_slice()), ([2, 3].as_slice(), [2, 3].as_slice()));
+        assert_eq!(cfg.loop_depth(2), 1);
+    }
+
+    #[test]
+    fn test_thousands_of_blocks() {
+        // 500 loops nested in each other: headers 0..500, body 500, latches 501..1000
+        let depth = 500;
+        let mut terminators: Vec<Terminator> = (0..=depth).map(|_| Terminator::FallThrough).collect();
+        terminators.extend((0..depth).map(|j| branch(depth - 1 - j)));
+        // Followed by 2000 loops in a row
+        let start = terminators.len();
+        for k in 0..2000 {
+            terminators.push(Terminator::FallThrough);
+            terminators.push(branch(start + 2 * k));
+        }
+        terminators.push(Terminator::Return);
+
+        let cfg = build(terminators);
+        assert_eq!(cfg.nodes.len(), 2 * depth + 4002);
+        assert_eq!(cfg.complexity.loop_count, depth + 2000);
+        assert_eq!(cfg.complexity.max_loop_depth, depth);
+        assert_eq!(cfg.loop_depth(depth), depth);
+        assert_eq!(cfg.loop_depth(start + 1), 1);
+        assert!(cfg.is_reducible());
+        assert_eq!(cfg.dominators.idom(start), Some(2 * depth));
+    }
+
+    #[test]
+    fn test_invalid_blocks_are_rejected() {
+        assert!(matches!(
+            ControlFlowGraphBuilder::build(Vec::new()),
+            Err(crate::dependency_analysis::analyzers::AnalysisError::EmptyInput)
+        ));
+        let dangling = vec![ControlFlowNode::new(0, Terminator::Jump(7))];
+        assert!(ControlFlowGraphBuilder::build(dangling).is_err());
+        let duplicate = vec![ControlFlowNode::new(0, Terminator::Return), ControlFlowNode::new(0, Terminator::Return)];
+        assert!(ControlFlowGraphBuilder::build(duplicate).is_err());
+    }
+}
@@ -17,25 +17,23 @@
 //! Reachability analysis to find unreachable blocks
 
 use super::ControlFlowGraph;
+use std::collections::HashSet;
 
 /// Finds nodes that are unreachable from the entry node
 pub struct ReachabilityAnalyzer;
 
 impl ReachabilityAnalyzer {
-    /// Returns IDs of unreachable nodes
+    /// Returns IDs of unreachable nodes, sorted
     pub fn find_unreachable(cfg: &ControlFlowGraph) -> Vec<usize> {
-        let mut visited = Vec::new();
+        let mut visited = HashSet::new();
         let mut stack = vec![cfg.entry_node];
         while let Some(n) = stack.pop() {
-            if !visited.contains(&n) {
-                visited.push(n);
-                for edge in &cfg.edges {
-                    if edge.from == n {
-                        stack.push(edge.to);
-                    }
-                }
+            if visited.insert(n) {
+                stack.extend(cfg.successors(n).iter().copied().filter(|succ| !visited.contains(succ)));
             }
         }
-        cfg.nodes.keys().filter(|&&n| !visited.contains(&n)).copied().collect()
+        let mut unreachable: Vec<usize> = cfg.nodes.keys().filter(|n| !visited.contains(n)).copied().collect();
+        unreachable.sort_unstable();
+        unreachable
     }
 }
@@ -17,6 +17,7 @@
 //! Definition-use (def-use) chain analysis
 
 /// Analyzer for def-use chains
+#[derive(Default)]
 pub struct DefUseAnalyzer;
 
 impl DefUseAnalyzer {
@@ -20,11 +20,13 @@ pub mod constant_prop;
 pub mod def_use;
 pub mod liveness;
 pub mod reaching;
+pub mod taint;
 
 pub use constant_prop::ConstantPropagator;
 pub use def_use::DefUseAnalyzer;
 pub use liveness::LivenessAnalyzer;
 pub use reaching::ReachingDefinitionsAnalyzer;
+pub use taint::{DataFlowAnalyzer, SUPPRESSION_ANNOTATION, TaintConfig};
 
 // Re-export types that were removed with legacy
 #[derive(Debug, Clone)]
@@ -34,16 +36,37 @@ pub struct DataFlowAnalysis {
     pub uses: Vec<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct DataFlowIssue {
     pub issue_type: DataFlowIssueType,
     pub location: String,
     pub description: String,
+    pub severity: IssueSeverity,
+    /// How the data got there, from where it entered to the location of the issue
+    pub path: Vec<FlowStep>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum DataFlowIssueType {
     UnusedVariable,
     UninitializedVariable,
     DeadCode,
+    /// Untrusted input reaches a state write or external call without being sanitized
+    TaintedStateWrite,
+}
+
+/// How serious a reported issue is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IssueSeverity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// One location data passes through
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlowStep {
+    /// Line number (1-based)
+    pub line: usize,
+    pub description: String,
 }
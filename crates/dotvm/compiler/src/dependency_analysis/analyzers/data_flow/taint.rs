@@ -0,0 +1,742 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Taint tracking from untrusted input to state writes and external calls
+//!
+//! [`DataFlowAnalyzer`] reads dot source one function at a time. Function parameters
+//! and host-input calls are sources. Their taint follows assignments, arithmetic and
+//! the arguments of any call that is not a sanitizer, and a value stored into a
+//! container or field taints the container as a whole. A sanitizer, called as a
+//! function or as a method on a value, yields a clean result.
+//!
+//! The analysis errs towards reporting: an assignment in the function body replaces
+//! a variable's taint, one inside a nested block only adds to it, and a loop body is
+//! read again until its taint settles, so a flow along any branch or iteration is
+//! found. Each source that reaches a sink is reported once per sink with its shortest
+//! path. A [`SUPPRESSION_ANNOTATION`] comment on the sink's line or the line above
+//! silences the report.
+
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use super::{DataFlowIssue, DataFlowIssueType, FlowStep, IssueSeverity};
+use crate::dependency_analysis::analyzers::source::{
+    Function, FunctionReader, KEYWORDS, SourceLine, assignment_operator, block_head, brace_balance, closing_paren, find_top_level, is_ident_byte, is_ident_start, leading_ident, pattern_names,
+    split_top_level, trailing_chain,
+};
+use crate::dependency_analysis::analyzers::{AnalysisResult, Analyzer};
+
+/// Comment marking a reviewed flow into the sink on its line or the next
+pub const SUPPRESSION_ANNOTATION: &str = "dataflow: allow(tainted_state_write)";
+
+/// Times a loop body is read again before its taint is taken as settled
+const MAX_LOOP_PASSES: usize = 32;
+
+/// Methods that store their arguments in the value they are called on
+const CONTAINER_WRITES: &[&str] = &["push", "push_back", "push_front", "push_str", "insert", "extend", "append"];
+
+/// Where untrusted data comes from, where it must not go, and what makes it safe
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaintConfig {
+    /// Host calls whose results are untrusted input
+    pub input_calls: Vec<String>,
+    /// Calls that write dot state
+    pub state_writes: Vec<String>,
+    /// Calls that hand data outside the dot, such as calls into other dots
+    pub external_calls: Vec<String>,
+    /// Calls whose results are trusted whatever their arguments
+    pub sanitizers: Vec<String>,
+    /// Severity of every reported flow
+    pub severity: IssueSeverity,
+}
+
+impl Default for TaintConfig {
+    fn default() -> Self {
+        Self {
+            input_calls: ["get_input", "read_input", "await_input"].map(String::from).to_vec(),
+            state_writes: vec!["set_state".to_string()],
+            external_calls: ["call_dot", "external_call"].map(String::from).to_vec(),
+            sanitizers: Vec::new(),
+            severity: IssueSeverity::Error,
+        }
+    }
+}
+
+impl TaintConfig {
+    /// Trust the results of these calls
+    pub fn with_sanitizers<I, S>(mut self, sanitizers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.sanitizers.extend(sanitizers.into_iter().map(Into::into));
+        self
+    }
+
+    /// Report flows with this severity
+    pub fn with_severity(mut self, severity: IssueSeverity) -> Self {
+        self.severity = severity;
+        self
+    }
+}
+
+/// Data flow analyzer reporting untrusted input that reaches a sink unsanitized
+pub struct DataFlowAnalyzer {
+    config: TaintConfig,
+    functions: FunctionReader,
+    sinks: Option<Regex>,
+}
+
+impl Default for DataFlowAnalyzer {
+    fn default() -> Self {
+        Self::new(TaintConfig::default())
+    }
+}
+
+impl DataFlowAnalyzer {
+    pub fn new(config: TaintConfig) -> Self {
+        let sink_names: Vec<_> = config.state_writes.iter().chain(&config.external_calls).map(|name| regex::escape(name)).collect();
+        Self {
+            functions: FunctionReader::new(),
+            sinks: (!sink_names.is_empty()).then(|| Regex::new(&format!(r"\b({})\s*\(", sink_names.join("|"))).expect("escaped sink names form a valid pattern")),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &TaintConfig {
+        &self.config
+    }
+
+    /// Every unsuppressed flow from a source to a sink, ordered by sink line
+    pub fn find_tainted_flows(&self, source: &str) -> Vec<DataFlowIssue> {
+        let lines = SourceLine::split(source);
+        let suppressed: HashSet<usize> = lines
+            .iter()
+            .filter(|line| line.comment.contains(SUPPRESSION_ANNOTATION))
+            .flat_map(|line| [line.number, line.number + 1])
+            .collect();
+
+        let mut flows = Flows::default();
+        for function in self.functions.functions(&lines) {
+            FunctionPass::new(self, &lines, &function, &mut flows).run();
+        }
+
+        let mut found: Vec<_> = flows.found.into_iter().filter(|(key, _)| !suppressed.contains(&key.sink_line)).collect();
+        found.sort_by(|(a, _), (b, _)| (a.sink_line, &a.sink, a.source).cmp(&(b.sink_line, &b.sink, b.source)));
+        found
+            .into_iter()
+            .map(|(key, flow)| DataFlowIssue {
+                issue_type: DataFlowIssueType::TaintedStateWrite,
+                location: format!("line {}", key.sink_line),
+                description: format!("{} reaches {} `{}` without passing a sanitizer", flows.sources[key.source], flow.kind, key.sink),
+                severity: self.config.severity,
+                path: flow.path,
+            })
+            .collect()
+    }
+}
+
+impl Analyzer for DataFlowAnalyzer {
+    type Result = Vec<DataFlowIssue>;
+
+    fn analyze(&self, input: &str) -> AnalysisResult<Self::Result> {
+        Ok(self.find_tainted_flows(input))
+    }
+
+    fn name(&self) -> &'static str {
+        "data_flow"
+    }
+}
+
+/// The shortest known path from each source, by source ID
+type Taint = BTreeMap<usize, Vec<FlowStep>>;
+
+/// Adds the paths of `from` that are new or shorter, returning whether `into` changed
+fn merge(into: &mut Taint, from: &Taint) -> bool {
+    let mut changed = false;
+    for (source, path) in from {
+        if into.get(source).is_none_or(|known| known.len() > path.len()) {
+            into.insert(*source, path.clone());
+            changed = true;
+        }
+    }
+    changed
+}
+
+fn extended(taint: &Taint, line: usize, description: String) -> Taint {
+    taint
+        .iter()
+        .map(|(source, path)| {
+            let mut path = path.clone();
+            path.push(FlowStep {
+                line,
+                description: description.clone(),
+            });
+            (*source, path)
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct FlowKey {
+    sink_line: usize,
+    sink: String,
+    source: usize,
+}
+
+struct Flow {
+    kind: &'static str,
+    path: Vec<FlowStep>,
+}
+
+/// Sources and flows found so far, across all functions
+#[derive(Default)]
+struct Flows {
+    sources: Vec<String>,
+    source_ids: HashMap<(usize, String), usize>,
+    found: HashMap<FlowKey, Flow>,
+}
+
+impl Flows {
+    /// The ID of the source described at `line`, the same each time a loop body is read
+    fn source(&mut self, line: usize, description: String) -> usize {
+        let next = self.sources.len();
+        let id = *self.source_ids.entry((line, description.clone())).or_insert(next);
+        if id == next {
+            self.sources.push(description);
+        }
+        id
+    }
+
+    fn record(&mut self, key: FlowKey, kind: &'static str, path: Vec<FlowStep>) {
+        if self.found.get(&key).is_none_or(|known| known.path.len() > path.len()) {
+            self.found.insert(key, Flow { kind, path });
+        }
+    }
+}
+
+enum BlockKind {
+    Plain,
+    Loop,
+    /// A match, with the taint of the value matched
+    Match(Taint),
+}
+
+struct Block {
+    kind: BlockKind,
+    /// Index of the line that opened the block
+    header: usize,
+    /// Variables once the block was entered
+    entry: HashMap<String, Taint>,
+}
+
+/// One walk over a function body
+struct FunctionPass<'a> {
+    analyzer: &'a DataFlowAnalyzer,
+    lines: &'a [SourceLine],
+    function: &'a Function,
+    flows: &'a mut Flows,
+    vars: HashMap<String, Taint>,
+    blocks: Vec<Block>,
+    loop_passes: HashMap<usize, usize>,
+}
+
+impl<'a> FunctionPass<'a> {
+    fn new(analyzer: &'a DataFlowAnalyzer, lines: &'a [SourceLine], function: &'a Function, flows: &'a mut Flows) -> Self {
+        let mut vars = HashMap::new();
+        for parameter in &function.parameters {
+            let source = flows.source(function.line, format!("Parameter `{}` of `{}`", parameter, function.name));
+            let step = FlowStep {
+                line: function.line,
+                description: format!("parameter `{}` of `{}`", parameter, function.name),
+            };
+            vars.insert(parameter.clone(), Taint::from([(source, vec![step])]));
+        }
+        Self {
+            analyzer,
+            lines,
+            function,
+            flows,
+            vars,
+            blocks: Vec::new(),
+            loop_passes: HashMap::new(),
+        }
+    }
+
+    fn config(&self) -> &'a TaintConfig {
+        let analyzer: &'a DataFlowAnalyzer = self.analyzer;
+        &analyzer.config
+    }
+
+    fn run(&mut self) {
+        let lines = self.lines;
+        let mut index = self.function.body_start;
+        // Set when a loop is read again from its header, whose leading braces were already closed
+        let mut rereading = false;
+        'lines: while index < self.function.body_end {
+            let number = lines[index].number;
+            let mut rest = lines[index].code.trim();
+
+            while let Some(after) = rest.strip_prefix('}') {
+                rest = after.trim_start();
+                if !rereading && let Some(header) = self.close_block() {
+                    index = header;
+                    rereading = true;
+                    continue 'lines;
+                }
+            }
+            rereading = false;
+
+            let mut kind = BlockKind::Plain;
+            for statement in split_top_level(rest, ';', false) {
+                if let Some(opened) = self.statement(&statement, number) {
+                    kind = opened;
+                }
+            }
+
+            let balance = brace_balance(rest);
+            if balance > 0 {
+                let entry = self.vars.clone();
+                self.blocks.push(Block { kind, header: index, entry });
+                for _ in 1..balance {
+                    let entry = self.vars.clone();
+                    self.blocks.push(Block {
+                        kind: BlockKind::Plain,
+                        header: index,
+                        entry,
+                    });
+                }
+            }
+            for _ in balance..0 {
+                if let Some(header) = self.close_block() {
+                    index = header;
+                    rereading = true;
+                    continue 'lines;
+                }
+            }
+            index += 1;
+        }
+    }
+
+    /// Leaves the innermost block, returning the header to read a loop again from if its
+    /// body changed any taint
+    fn close_block(&mut self) -> Option<usize> {
+        let block = self.blocks.pop()?;
+        if matches!(block.kind, BlockKind::Loop) && self.vars != block.entry {
+            let passes = self.loop_passes.entry(block.header).or_default();
+            if *passes < MAX_LOOP_PASSES {
+                *passes += 1;
+                return Some(block.header);
+            }
+        }
+        None
+    }
+
+    /// Applies one statement, returning the kind of block it opens if it is a header
+    fn statement(&mut self, statement: &str, line: usize) -> Option<BlockKind> {
+        let mut statement = statement.trim();
+
+        if let Some(BlockKind::Match(matched)) = self.blocks.last().map(|block| &block.kind)
+            && let Some(arrow) = find_top_level(statement, "=>")
+        {
+            let matched = matched.clone();
+            let pattern = statement[..arrow].split(" if ").next().unwrap_or_default();
+            for name in pattern_names(pattern) {
+                let taint = extended(&matched, line, format!("matched as `{}`", name));
+                self.assign(&name, &taint, false);
+            }
+            statement = statement[arrow + 2..].trim();
+        }
+
+        let header = statement.strip_prefix("else").map(str::trim_start).unwrap_or(statement);
+        let (kind, binding) = if let Some(rest) = header.strip_prefix("for ") {
+            let (pattern, iterated) = rest.split_once(" in ").unwrap_or((rest, ""));
+            (Some(BlockKind::Loop), Some((pattern, iterated)))
+        } else if let Some(rest) = header.strip_prefix("while let ") {
+            (Some(BlockKind::Loop), rest.split_once('='))
+        } else if header.starts_with("while ") || header.starts_with("loop") {
+            (Some(BlockKind::Loop), None)
+        } else if let Some(rest) = header.strip_prefix("if let ") {
+            (None, rest.split_once('='))
+        } else if let Some(matched) = header.strip_prefix("match ").or_else(|| header.split_once("= match ").map(|(_, matched)| matched)) {
+            (Some(BlockKind::Match(self.taint_of(block_head(matched), line))), None)
+        } else {
+            (None, None)
+        };
+        if let Some((pattern, value)) = binding {
+            let taint = self.taint_of(block_head(value), line);
+            for name in pattern_names(pattern) {
+                let bound = extended(&taint, line, format!("bound to `{}`", name));
+                self.assign(&name, &bound, false);
+            }
+        }
+
+        self.report_sinks(statement, line);
+        self.container_writes(statement, line);
+        if binding.is_none() {
+            self.assignment(statement, line);
+        }
+        kind
+    }
+
+    fn report_sinks(&mut self, statement: &str, line: usize) {
+        let analyzer: &'a DataFlowAnalyzer = self.analyzer;
+        let Some(sinks) = &analyzer.sinks else {
+            return;
+        };
+        for sink in sinks.captures_iter(statement) {
+            let (call, name) = (sink.get(0).expect("a match has a whole"), &sink[1]);
+            let Some(close) = closing_paren(&statement[call.end()..]) else {
+                continue;
+            };
+            let kind = if self.config().state_writes.iter().any(|write| write == name) {
+                "state write"
+            } else {
+                "external call"
+            };
+            let taint = self.taint_of(&statement[call.end()..call.end() + close], line);
+            for (source, path) in extended(&taint, line, format!("passed to `{}`", name)) {
+                let key = FlowKey {
+                    sink_line: line,
+                    sink: name.to_string(),
+                    source,
+                };
+                self.flows.record(key, kind, path);
+            }
+        }
+    }
+
+    /// Taints containers by what is pushed or inserted into them
+    fn container_writes(&mut self, statement: &str, line: usize) {
+        for method in CONTAINER_WRITES {
+            let call = format!(".{}(", method);
+            for (at, _) in statement.match_indices(&call) {
+                let Some(base) = leading_ident(trailing_chain(&statement[..at])) else {
+                    continue;
+                };
+                let arguments = &statement[at + call.len()..];
+                let Some(close) = closing_paren(arguments) else {
+                    continue;
+                };
+                let taint = self.taint_of(&arguments[..close], line);
+                let stored = extended(&taint, line, format!("stored in `{}`", base));
+                self.assign(base, &stored, false);
+            }
+        }
+    }
+
+    /// Applies `let`, plain and compound assignments
+    fn assignment(&mut self, statement: &str, line: usize) {
+        let strong = self.blocks.is_empty();
+        if let Some(rest) = statement.strip_prefix("let ") {
+            let Some((at, len, _)) = assignment_operator(rest) else {
+                return;
+            };
+            let pattern = split_top_level(&rest[..at], ':', false).into_iter().next().unwrap_or_default();
+            let taint = self.taint_of(&rest[at + len..], line);
+            for name in pattern_names(&pattern) {
+                let assigned = extended(&taint, line, format!("assigned to `{}`", name));
+                self.assign(&name, &assigned, strong);
+            }
+            return;
+        }
+
+        let Some((at, len, compound)) = assignment_operator(statement) else {
+            return;
+        };
+        let target = statement[..at].trim();
+        let Some(base) = leading_ident(target) else {
+            return;
+        };
+        let taint = self.taint_of(&statement[at + len..], line);
+        // Writing through a field, index or reference keeps whatever else the value held
+        let whole = target == base;
+        let step = if whole { format!("assigned to `{}`", base) } else { format!("stored in `{}`", base) };
+        self.assign(base, &extended(&taint, line, step), strong && whole && !compound);
+    }
+
+    /// Sets a variable's taint, or adds to it when the assignment may not happen
+    fn assign(&mut self, name: &str, taint: &Taint, strong: bool) {
+        if strong {
+            self.vars.insert(name.to_string(), taint.clone());
+        } else {
+            merge(self.vars.entry(name.to_string()).or_default(), taint);
+        }
+    }
+
+    /// The taint of an expression
+    fn taint_of(&mut self, expression: &str, line: usize) -> Taint {
+        let config = self.config();
+        let bytes = expression.as_bytes();
+        let mut taint = Taint::new();
+        let mut at = 0;
+        while at < bytes.len() {
+            if !is_ident_start(bytes[at]) || (at > 0 && is_ident_byte(bytes[at - 1])) {
+                at += 1;
+                continue;
+            }
+            let start = at;
+            while at < bytes.len() && is_ident_byte(bytes[at]) {
+                at += 1;
+            }
+            let name = &expression[start..at];
+            let before = expression[..start].trim_end();
+            let after = expression[at..].trim_start();
+            let after_at = expression.len() - after.len();
+
+            if before.ends_with('.') && !before.ends_with("..") {
+                // A field or method, already covered by the value it belongs to
+                continue;
+            }
+            if let Some(arguments) = after.strip_prefix('(') {
+                let (close, end) = match closing_paren(arguments) {
+                    Some(close) => (close, after_at + close + 2),
+                    None => (arguments.len(), expression.len()),
+                };
+                if config.sanitizers.iter().any(|sanitizer| sanitizer == name) {
+                    at = end;
+                    continue;
+                }
+                let mut value = self.taint_of(&arguments[..close], line);
+                if config.input_calls.iter().any(|input| input == name) {
+                    let source = self.flows.source(line, format!("Input from `{}` on line {}", name, line));
+                    let step = FlowStep {
+                        line,
+                        description: format!("input from `{}`", name),
+                    };
+                    value.insert(source, vec![step]);
+                }
+                at = end;
+                match self.sanitized_from(expression, at) {
+                    Some(end) => at = end,
+                    None => {
+                        merge(&mut taint, &value);
+                    }
+                }
+                continue;
+            }
+            if after.starts_with("::") || after.starts_with('!') || KEYWORDS.contains(&name) || name.starts_with(|c: char| c.is_ascii_uppercase()) || before.ends_with("::") {
+                continue;
+            }
+            match self.sanitized_from(expression, at) {
+                Some(end) => at = end,
+                None => {
+                    if let Some(value) = self.vars.get(name) {
+                        merge(&mut taint, value);
+                    }
+                }
+            }
+        }
+        taint
+    }
+
+    /// The end of a sanitizer method called on the value ending at `at`
+    fn sanitized_from(&self, expression: &str, at: usize) -> Option<usize> {
+        let rest = expression[at..].trim_start().strip_prefix('.')?.trim_start();
+        let name_len = rest.bytes().take_while(|&b| is_ident_byte(b)).count();
+        if !self.config().sanitizers.iter().any(|sanitizer| sanitizer == &rest[..name_len]) {
+            return None;
+        }
+        let arguments = rest[name_len..].trim_start().strip_prefix('(')?;
+        let close = closing_paren(arguments)?;
+        Some(expression.len() - arguments.len() + close + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flows(source: &str) -> Vec<DataFlowIssue> {
+        DataFlowAnalyzer::default().find_tainted_flows(source)
+    }
+
+    fn steps(issue: &DataFlowIssue) -> Vec<(usize, &str)> {
+        issue.path.iter().map(|step| (step.line, step.description.as_str())).collect()
+    }
+
+    #[test]
+    fn test_parameter_reaches_state_write_through_assignments() {
+        let issues = flows(
+            "fn deposit(amount: u64) {
+    let fee = amount / 100;
+    let mut total = get_state(\"total\");
+    total = total + amount - fee;
+    set_state(\"total\", total);
+}",
+        );
+
+        assert_eq!(issues.len(), 1);
+        let issue = &issues[0];
+        assert_eq!(
+            (issue.issue_type.clone(), issue.severity, issue.location.as_str()),
+            (DataFlowIssueType::TaintedStateWrite, IssueSeverity::Error, "line 5")
+        );
+        assert_eq!(issue.description, "Parameter `amount` of `deposit` reaches state write `set_state` without passing a sanitizer");
+        assert_eq!(steps(issue), [(1, "parameter `amount` of `deposit`"), (4, "assigned to `total`"), (5, "passed to `set_state`")]);
+    }
+
+    #[test]
+    fn test_host_input_reaches_external_call_through_container() {
+        let issues = flows(
+            "fn forward() {
+    let request = get_input();
+    let mut batch = Vec::new();
+    for item in request.items() {
+        batch.push(item * 2);
+    }
+    call_dot(\"ledger\", batch);
+}",
+        );
+
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].description, "Input from `get_input` on line 2 reaches external call `call_dot` without passing a sanitizer");
+        assert_eq!(
+            steps(&issues[0]),
+            [
+                (2, "input from `get_input`"),
+                (2, "assigned to `request`"),
+                (4, "bound to `item`"),
+                (5, "stored in `batch`"),
+                (7, "passed to `call_dot`")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sanitizer_breaks_the_chain() {
+        let analyzer = DataFlowAnalyzer::new(TaintConfig::default().with_sanitizers(["validate_amount", "clamp_to_limit"]));
+        let source = "fn deposit(amount: u64, note: String) {
+    let checked = validate_amount(amount);
+    set_state(\"amount\", checked);
+    let limited = note.len().min(amount.clamp_to_limit());
+    set_state(\"limited\", limited);
+    let raw = amount;
+    set_state(\"raw\", raw);
+}";
+
+        // `note.len()` still carries the note's taint; only the raw amount and the note are reported
+        let issues = analyzer.find_tainted_flows(source);
+        let reported: Vec<_> = issues.iter().map(|issue| (issue.location.as_str(), issue.path[0].description.as_str())).collect();
+        assert_eq!(reported, [("line 5", "parameter `note` of `deposit`"), ("line 7", "parameter `amount` of `deposit`")]);
+    }
+
+    #[test]
+    fn test_reassignment_in_function_body_clears_taint_but_branches_do_not() {
+        let issues = flows(
+            "fn update(value: u64, flag: bool) {
+    let mut stored = value;
+    stored = 0;
+    set_state(\"cleared\", stored);
+    if flag {
+        stored = value;
+    } else {
+        stored = 1;
+    }
+    set_state(\"branch\", stored);
+}",
+        );
+
+        assert_eq!(issues.len(), 1);
+        assert_eq!(steps(&issues[0]), [(1, "parameter `value` of `update`"), (6, "assigned to `stored`"), (10, "passed to `set_state`")]);
+    }
+
+    #[test]
+    fn test_loop_carried_flow_is_found() {
+        let issues = flows(
+            "fn run() {
+    let mut previous = 0;
+    let mut current = 0;
+    loop {
+        set_state(\"previous\", previous);
+        previous = current;
+        current = read_input();
+    }
+}",
+        );
+
+        assert_eq!(issues.len(), 1);
+        assert_eq!(
+            steps(&issues[0]),
+            [
+                (7, "input from `read_input`"),
+                (7, "assigned to `current`"),
+                (6, "assigned to `previous`"),
+                (5, "passed to `set_state`")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shortest_path_is_reported_once_per_source() {
+        let issues = flows(
+            "fn mix(a: u64, b: u64) {
+    let x = a;
+    let y = x * 2;
+    let z = y + x;
+    set_state(\"z\", z + b);
+}",
+        );
+
+        assert_eq!(issues.len(), 2);
+        assert_eq!(
+            steps(&issues[0]),
+            [(1, "parameter `a` of `mix`"), (2, "assigned to `x`"), (4, "assigned to `z`"), (5, "passed to `set_state`")]
+        );
+        assert_eq!(steps(&issues[1]), [(1, "parameter `b` of `mix`"), (5, "passed to `set_state`")]);
+    }
+
+    #[test]
+    fn test_match_arms_and_suppression() {
+        let source = "fn apply(command: Command) {
+    match command {
+        Command::Set(value) => set_state(\"value\", value),
+        Command::Reset => set_state(\"value\", 0),
+    }
+    // dataflow: allow(tainted_state_write) -- the owner is checked by the host
+    set_state(\"owner\", command);
+    set_state(\"echo\", command); // dataflow: allow(tainted_state_write)
+}";
+
+        let issues = flows(source);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(steps(&issues[0]), [(1, "parameter `command` of `apply`"), (3, "matched as `value`"), (3, "passed to `set_state`")]);
+    }
+
+    #[test]
+    fn test_configured_sinks_and_severity() {
+        let config = TaintConfig {
+            state_writes: vec!["put".to_string()],
+            external_calls: Vec::new(),
+            ..TaintConfig::default()
+        }
+        .with_severity(IssueSeverity::Warning);
+        let analyzer = DataFlowAnalyzer::new(config);
+        let source = "impl Store {
+    fn save(&self, key: String) {
+        self.db.put(\"key\", key);
+        set_state(\"key\", key);
+        // put(\"commented\", key);
+        log(\"put(key)\");
+    }
+}";
+
+        let issues = analyzer.analyze(source).unwrap();
+        assert_eq!(issues.len(), 1);
+        assert_eq!((issues[0].location.as_str(), issues[0].severity), ("line 3", IssueSeverity::Warning));
+        assert_eq!(analyzer.name(), "data_flow");
+    }
+}
@@ -31,15 +31,15 @@
 //!
 //! ### Data Flow Analysis (`data_flow`)
 //! - **Purpose**: Tracks how data flows through the program
-//! - **Capabilities**: Definition-use chains, liveness analysis, reaching definitions
-//! - **Use Cases**: Variable optimization, constant propagation, dead code elimination
-//! - **Output**: Data flow information, variable lifetime data
+//! - **Capabilities**: Definition-use chains, liveness analysis, reaching definitions, taint tracking
+//! - **Use Cases**: Variable optimization, constant propagation, dead code elimination, untrusted input reaching state writes
+//! - **Output**: Data flow information, variable lifetime data, source-to-sink flow paths
 //!
 //! ### State Access Analysis (`state_access`)
 //! - **Purpose**: Analyzes state access patterns
-//! - **Capabilities**: Read/write tracking, conflict detection, optimization hints
+//! - **Capabilities**: Read/write tracking, conflict detection, reentrancy detection across calls, optimization hints
 //! - **Use Cases**: Security analysis, reentrancy detection
-//! - **Output**: State access patterns, conflict reports, optimization suggestions
+//! - **Output**: State access patterns, conflict reports with call paths, optimization suggestions
 //!
 //! ## Common Analysis Framework
 //!
@@ -66,6 +66,7 @@
 
 pub mod control_flow;
 pub mod data_flow;
+mod source;
 pub mod state_access;
 
 // Re-export common types
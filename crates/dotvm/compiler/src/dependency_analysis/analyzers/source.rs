@@ -0,0 +1,327 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Reading dot source for the source-level analyzers
+//!
+//! Dot source is Rust-like. These helpers split it into lines with string contents
+//! blanked out, find the functions in it and pick expressions apart just far enough
+//! for the analyzers to follow values through assignments and calls.
+
+use regex::Regex;
+use std::ops::Range;
+
+pub(crate) const KEYWORDS: &[&str] = &[
+    "as", "break", "continue", "else", "false", "fn", "for", "if", "in", "let", "loop", "match", "move", "mut", "ref", "return", "true", "while",
+];
+
+/// A source line with string contents blanked out and its comment split off
+pub(crate) struct SourceLine {
+    /// Line number (1-based)
+    pub(crate) number: usize,
+    /// The code with each byte of a string's contents replaced by a space, so offsets into it
+    /// are offsets into `text` too
+    pub(crate) code: String,
+    pub(crate) comment: String,
+    /// The line as written
+    pub(crate) text: String,
+}
+
+impl SourceLine {
+    pub(crate) fn split(source: &str) -> Vec<SourceLine> {
+        source
+            .lines()
+            .enumerate()
+            .map(|(index, line)| {
+                let mut code = String::with_capacity(line.len());
+                let mut comment = String::new();
+                let mut in_string = false;
+                let mut escaped = false;
+                let mut chars = line.char_indices().peekable();
+                while let Some((at, c)) = chars.next() {
+                    if in_string {
+                        let closes = c == '"' && !escaped;
+                        if closes {
+                            code.push('"');
+                        } else {
+                            code.extend(std::iter::repeat_n(' ', c.len_utf8()));
+                        }
+                        escaped = c == '\\' && !escaped;
+                        in_string = !closes;
+                    } else if c == '/' && chars.peek().is_some_and(|&(_, next)| next == '/') {
+                        comment = line[at..].to_string();
+                        break;
+                    } else {
+                        in_string = c == '"';
+                        code.push(c);
+                    }
+                }
+                SourceLine {
+                    number: index + 1,
+                    code,
+                    comment,
+                    text: line.to_string(),
+                }
+            })
+            .collect()
+    }
+}
+
+pub(crate) struct Function {
+    pub(crate) name: String,
+    /// Line number of the header
+    pub(crate) line: usize,
+    pub(crate) parameters: Vec<String>,
+    /// Index of the first line after the opening brace
+    pub(crate) body_start: usize,
+    /// Index of the line with the closing brace
+    pub(crate) body_end: usize,
+}
+
+/// Finds function definitions in split source lines
+pub(crate) struct FunctionReader {
+    header: Regex,
+}
+
+impl FunctionReader {
+    pub(crate) fn new() -> Self {
+        Self {
+            header: Regex::new(r"\bfn\s+([A-Za-z_][A-Za-z0-9_]*)\s*(?:<[^>]*>)?\s*\(").expect("the function header pattern is valid"),
+        }
+    }
+
+    /// Every function with a body, in source order
+    pub(crate) fn functions(&self, lines: &[SourceLine]) -> Vec<Function> {
+        let mut functions = Vec::new();
+        let mut index = 0;
+        while index < lines.len() {
+            match self.function_at(lines, index) {
+                Some(function) => {
+                    index = function.body_end + 1;
+                    functions.push(function);
+                }
+                None => index += 1,
+            }
+        }
+        functions
+    }
+
+    /// The function whose header is on line `index`, if there is one
+    fn function_at(&self, lines: &[SourceLine], index: usize) -> Option<Function> {
+        let header = self.header.captures(&lines[index].code)?;
+        let name = header[1].to_string();
+
+        // The parameter list and the opening brace may be on later lines
+        let mut signature = lines[index].code[header.get(0)?.end()..].to_string();
+        let mut open_line = index;
+        while !signature.contains('{') {
+            if signature.contains(';') {
+                return None;
+            }
+            open_line += 1;
+            signature.push(' ');
+            signature.push_str(&lines.get(open_line)?.code);
+        }
+        let close = closing_paren(&signature)?;
+        let parameters = split_top_level(&signature[..close], ',', true)
+            .iter()
+            .filter_map(|parameter| split_top_level(parameter, ':', false).into_iter().next())
+            .flat_map(|pattern| pattern_names(&pattern))
+            .filter(|name| name != "self")
+            .collect();
+
+        let mut depth = 0;
+        for (offset, line) in lines[open_line..].iter().enumerate() {
+            depth += brace_balance(&line.code);
+            if depth <= 0 {
+                return Some(Function {
+                    name,
+                    line: lines[index].number,
+                    parameters,
+                    body_start: open_line + 1,
+                    body_end: open_line + offset,
+                });
+            }
+        }
+        None
+    }
+}
+
+pub(crate) fn is_ident_start(byte: u8) -> bool {
+    byte.is_ascii_alphabetic() || byte == b'_'
+}
+
+pub(crate) fn is_ident_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+pub(crate) fn brace_balance(code: &str) -> i64 {
+    code.matches('{').count() as i64 - code.matches('}').count() as i64
+}
+
+/// The offset of the `)` closing a parenthesis opened just before `text`
+pub(crate) fn closing_paren(text: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    for (at, byte) in text.bytes().enumerate() {
+        match byte {
+            b'(' => depth += 1,
+            b')' if depth == 0 => return Some(at),
+            b')' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits `text` at `separator` outside brackets, and outside angle brackets if `angles` is set
+pub(crate) fn split_top_level(text: &str, separator: char, angles: bool) -> Vec<String> {
+    top_level_ranges(text, separator, angles).into_iter().map(|range| text[range].to_string()).collect()
+}
+
+/// Where `split_top_level` would split `text`, as the ranges of the trimmed, non-empty pieces
+pub(crate) fn top_level_ranges(text: &str, separator: char, angles: bool) -> Vec<Range<usize>> {
+    let bytes = text.as_bytes();
+    let mut pieces = Vec::new();
+    let mut depth = 0i64;
+    let mut start = 0;
+    for (at, &byte) in bytes.iter().enumerate() {
+        let previous = at.checked_sub(1).map(|before| bytes[before]);
+        match byte {
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' => depth -= 1,
+            b'<' if angles => depth += 1,
+            b'>' if angles && !matches!(previous, Some(b'-' | b'=')) => depth -= 1,
+            _ if byte == separator as u8 && depth <= 0 => {
+                // `::` is a path, not a separator
+                if separator == ':' && (previous == Some(b':') || bytes.get(at + 1) == Some(&b':')) {
+                    continue;
+                }
+                pieces.push(trimmed(text, start..at));
+                start = at + 1;
+            }
+            _ => {}
+        }
+    }
+    pieces.push(trimmed(text, start..text.len()));
+    pieces.retain(|piece| !piece.is_empty());
+    pieces
+}
+
+/// `range` of `text` without the whitespace at either end
+pub(crate) fn trimmed(text: &str, range: Range<usize>) -> Range<usize> {
+    let piece = &text[range.clone()];
+    let start = range.start + (piece.len() - piece.trim_start().len());
+    let end = range.end - (piece.len() - piece.trim_end().len());
+    start..end.max(start)
+}
+
+/// The offset of `needle` outside brackets
+pub(crate) fn find_top_level(text: &str, needle: &str) -> Option<usize> {
+    let mut depth = 0i64;
+    for (at, byte) in text.bytes().enumerate() {
+        match byte {
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' => depth -= 1,
+            _ if depth == 0 && text[at..].starts_with(needle) => return Some(at),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The offset and length of the assignment operator outside brackets, and whether it is compound
+pub(crate) fn assignment_operator(text: &str) -> Option<(usize, usize, bool)> {
+    let bytes = text.as_bytes();
+    let mut depth = 0i64;
+    for (at, &byte) in bytes.iter().enumerate() {
+        match byte {
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' => depth -= 1,
+            b'=' if depth == 0 => {
+                let previous = at.checked_sub(1).map(|before| bytes[before]);
+                let next = bytes.get(at + 1).copied();
+                if matches!(next, Some(b'=' | b'>')) || previous == Some(b'=') {
+                    continue;
+                }
+                match previous {
+                    Some(b'+' | b'-' | b'*' | b'/' | b'%' | b'^' | b'|' | b'&') => return Some((at - 1, 2, true)),
+                    Some(shift @ (b'<' | b'>')) if at >= 2 && bytes[at - 2] == shift => return Some((at - 2, 3, true)),
+                    Some(b'<' | b'>' | b'!' | b'.') => continue,
+                    _ => return Some((at, 1, false)),
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The receiver chain ending `text`, such as `self.items[key]`
+pub(crate) fn trailing_chain(text: &str) -> &str {
+    let bytes = text.as_bytes();
+    let mut depth = 0i64;
+    let mut start = bytes.len();
+    while start > 0 {
+        let byte = bytes[start - 1];
+        match byte {
+            b']' | b')' => depth += 1,
+            b'[' | b'(' if depth > 0 => depth -= 1,
+            _ if depth > 0 || is_ident_byte(byte) || byte == b'.' => {}
+            _ => break,
+        }
+        start -= 1;
+    }
+    &text[start..]
+}
+
+/// The variable a place expression such as `*total` or `self.items[key]` belongs to
+pub(crate) fn leading_ident(text: &str) -> Option<&str> {
+    let text = text.trim_start_matches(|c: char| matches!(c, '*' | '&' | '(') || c.is_whitespace());
+    let text = text.strip_prefix("mut ").unwrap_or(text);
+    let len = text.bytes().take_while(|&byte| is_ident_byte(byte)).count();
+    let name = &text[..len];
+    (!name.is_empty() && is_ident_start(name.as_bytes()[0]) && !KEYWORDS.contains(&name)).then_some(name)
+}
+
+/// The expression before the brace that opens a block header's body
+pub(crate) fn block_head(text: &str) -> &str {
+    let text = text.trim_end();
+    text.strip_suffix('{').unwrap_or(text)
+}
+
+/// The variables a pattern binds
+pub(crate) fn pattern_names(pattern: &str) -> Vec<String> {
+    let bytes = pattern.as_bytes();
+    let mut names = Vec::new();
+    let mut at = 0;
+    while at < bytes.len() {
+        if !is_ident_start(bytes[at]) || (at > 0 && is_ident_byte(bytes[at - 1])) {
+            at += 1;
+            continue;
+        }
+        let start = at;
+        while at < bytes.len() && is_ident_byte(bytes[at]) {
+            at += 1;
+        }
+        let name = &pattern[start..at];
+        let after = pattern[at..].trim_start();
+        // Variants, paths and struct field names are not bindings
+        let binds = !(after.starts_with(['(', '{', ':']) || pattern[..start].trim_end().ends_with("::"));
+        if binds && name != "_" && !KEYWORDS.contains(&name) && !name.starts_with(|c: char| c.is_ascii_uppercase()) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
@@ -38,12 +38,32 @@ pub struct StateAccess {
 }
 
 /// Represents a potential state access conflict
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConflictSeverity {
+    Low,
+    Medium,
+    High,
+}
+
+/// One access or call on the way from a conflict's first access to its second
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessStep {
+    /// Function the step happens in
+    pub function: String,
+    /// Line number (1-based)
+    pub line: usize,
+    pub description: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct StateConflict {
     pub location: String,
     pub first_access: StateAccessType,
     pub second_access: StateAccessType,
     pub description: String,
+    pub severity: ConflictSeverity,
+    /// Steps from the first access to the second, across the functions they pass through
+    pub path: Vec<AccessStep>,
 }
 
 /// Detects state access conflicts
@@ -19,7 +19,9 @@
 pub mod conflicts;
 pub mod optimization;
 pub mod read_write;
+pub mod reentrancy;
 
-pub use conflicts::{ConflictDetector, StateAccess, StateAccessType, StateConflict};
+pub use conflicts::{AccessStep, ConflictDetector, ConflictSeverity, StateAccess, StateAccessType, StateConflict};
 pub use optimization::AccessOptimizationHints;
 pub use read_write::ReadWriteAnalyzer;
+pub use reentrancy::{ReentrancyAnalyzer, ReentrancyConfig};
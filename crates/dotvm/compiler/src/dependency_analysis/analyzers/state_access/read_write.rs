@@ -17,6 +17,7 @@
 //! Read/write pattern analysis for state access
 
 /// Analyzes read/write patterns in code
+#[derive(Default)]
 pub struct ReadWriteAnalyzer;
 
 impl ReadWriteAnalyzer {
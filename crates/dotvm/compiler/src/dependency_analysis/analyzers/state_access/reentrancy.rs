@@ -0,0 +1,683 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Reentrancy: state read, external call, then a write of the stale value
+//!
+//! [`ReentrancyAnalyzer`] reads dot source and builds a call graph of its functions. A
+//! function reaches an external call if it makes one or calls a function that does,
+//! and a call to such a function counts as an external call. Within a function, a
+//! value read from state and written back to the same state after an external call
+//! is reported: the called dot can call back in and change that state in between,
+//! and the write then overwrites the change. A write made before the call, as
+//! checks-effects-interactions prescribes, is not reported, nor is one whose value
+//! was read again after the call.
+//!
+//! A call to one of the configured guard functions before the external call
+//! suppresses the report, as does the function only ever being called after a guard.
+//! Statements are taken in source order, so an external call in one branch and a
+//! write in a later one are taken as sequential and the analysis errs towards
+//! reporting.
+
+use petgraph::Direction;
+use petgraph::graph::NodeIndex;
+use regex::Regex;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::Range;
+
+use super::conflicts::{AccessStep, ConflictSeverity, StateAccessType, StateConflict};
+use crate::dependency_analysis::analyzers::source::{
+    Function, FunctionReader, KEYWORDS, SourceLine, assignment_operator, brace_balance, closing_paren, is_ident_byte, is_ident_start, leading_ident, pattern_names, split_top_level, top_level_ranges,
+};
+use crate::dependency_analysis::analyzers::{AnalysisResult, Analyzer};
+use crate::dependency_analysis::core::graph::{DependencyGraph, GraphBuilder};
+
+/// Which calls touch state, which leave the dot and which guard against re-entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReentrancyConfig {
+    /// Calls that read dot state, keyed by their first argument
+    pub state_reads: Vec<String>,
+    /// Calls that write dot state, keyed by their first argument
+    pub state_writes: Vec<String>,
+    /// Calls into other dots or the host that may call back into this dot
+    pub external_calls: Vec<String>,
+    /// Calls that keep the calling function from being re-entered until it returns
+    pub guards: Vec<String>,
+}
+
+impl Default for ReentrancyConfig {
+    fn default() -> Self {
+        Self {
+            state_reads: vec!["get_state".to_string()],
+            state_writes: vec!["set_state".to_string()],
+            external_calls: ["call_dot", "external_call"].map(String::from).to_vec(),
+            guards: ["reentrancy_guard", "non_reentrant"].map(String::from).to_vec(),
+        }
+    }
+}
+
+impl ReentrancyConfig {
+    /// Also treat these calls as reentrancy guards
+    pub fn with_guards<I, S>(mut self, guards: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.guards.extend(guards.into_iter().map(Into::into));
+        self
+    }
+}
+
+fn named(names: &[String], name: &str) -> bool {
+    names.iter().any(|known| known == name)
+}
+
+/// State access analyzer reporting state written from a value read before an external call
+pub struct ReentrancyAnalyzer {
+    config: ReentrancyConfig,
+    functions: FunctionReader,
+    calls: Regex,
+}
+
+impl Default for ReentrancyAnalyzer {
+    fn default() -> Self {
+        Self::new(ReentrancyConfig::default())
+    }
+}
+
+impl ReentrancyAnalyzer {
+    pub fn new(config: ReentrancyConfig) -> Self {
+        Self {
+            config,
+            functions: FunctionReader::new(),
+            calls: Regex::new(r"\b([A-Za-z_][A-Za-z0-9_]*)\s*\(").expect("the call pattern is valid"),
+        }
+    }
+
+    pub fn config(&self) -> &ReentrancyConfig {
+        &self.config
+    }
+
+    /// Every unguarded read, external call and dependent write, ordered by the line of the write
+    pub fn find_reentrancy(&self, source: &str) -> Vec<StateConflict> {
+        let lines = SourceLine::split(source);
+        let functions = self.functions.functions(&lines);
+        let bodies: Vec<_> = functions.iter().map(|function| statements(&lines, function)).collect();
+        let sites: Vec<_> = bodies.iter().map(|body| self.call_sites(body)).collect();
+        let calls = CallGraph::build(&self.config, &functions, &sites);
+
+        let mut conflicts = Vec::new();
+        for ((function, body), sites) in functions.iter().zip(&bodies).zip(&sites) {
+            if calls.guarded.contains(&function.name) {
+                continue;
+            }
+            FunctionPass::new(self, function, sites, &calls).run(body, &mut conflicts);
+        }
+        conflicts.sort_by_key(|conflict| conflict.path.last().map(|step| step.line));
+        conflicts
+    }
+
+    /// Every call made in a function body, in order
+    fn call_sites(&self, body: &[Statement]) -> Vec<CallSite> {
+        let mut sites = Vec::new();
+        for statement in body {
+            for call in self.calls.captures_iter(statement.code) {
+                let name = &call[1];
+                let before = &statement.code[..call.get(0).expect("a match has a whole").start()];
+                if KEYWORDS.contains(&name) || before.split_whitespace().next_back() == Some("fn") {
+                    continue;
+                }
+                sites.push(CallSite {
+                    name: name.to_string(),
+                    line: statement.line,
+                    position: call_position(statement.index),
+                });
+            }
+        }
+        sites
+    }
+}
+
+impl Analyzer for ReentrancyAnalyzer {
+    type Result = Vec<StateConflict>;
+
+    fn analyze(&self, input: &str) -> AnalysisResult<Self::Result> {
+        Ok(self.find_reentrancy(input))
+    }
+
+    fn name(&self) -> &'static str {
+        "reentrancy"
+    }
+}
+
+// Each statement has three positions, so that what one statement reads comes before the
+// calls it makes, and those come before what it writes
+fn read_position(statement: usize) -> usize {
+    statement * 3
+}
+
+fn call_position(statement: usize) -> usize {
+    statement * 3 + 1
+}
+
+fn write_position(statement: usize) -> usize {
+    statement * 3 + 2
+}
+
+/// A statement in a function body
+struct Statement<'a> {
+    /// Line number (1-based)
+    line: usize,
+    code: &'a str,
+    /// The statement as written, at the same offsets as `code`
+    text: &'a str,
+    /// Index among the statements of the function
+    index: usize,
+    /// Blocks the statement is nested in within the function body
+    depth: i64,
+}
+
+impl Statement<'_> {
+    /// The written text of `range`, with string contents intact
+    fn text_of(&self, range: Range<usize>) -> &str {
+        self.text.get(range.clone()).unwrap_or(&self.code[range]).trim()
+    }
+}
+
+fn statements<'a>(lines: &'a [SourceLine], function: &Function) -> Vec<Statement<'a>> {
+    let mut statements = Vec::new();
+    let mut depth = 0i64;
+    for line in lines.get(function.body_start..function.body_end).unwrap_or_default() {
+        let code = line.code.as_str();
+        let rest = code.trim_start_matches(|c: char| c == '}' || c.is_whitespace());
+        let offset = code.len() - rest.len();
+        depth -= code[..offset].matches('}').count() as i64;
+        for range in top_level_ranges(rest, ';', false) {
+            let range = range.start + offset..range.end + offset;
+            statements.push(Statement {
+                line: line.number,
+                code: &code[range.clone()],
+                text: line.text.get(range.clone()).unwrap_or(&code[range]),
+                index: statements.len(),
+                depth: depth.max(0),
+            });
+        }
+        depth += brace_balance(rest);
+    }
+    statements
+}
+
+struct CallSite {
+    name: String,
+    line: usize,
+    position: usize,
+}
+
+impl CallSite {
+    fn step(&self, function: &str) -> AccessStep {
+        AccessStep {
+            function: function.to_string(),
+            line: self.line,
+            description: format!("calls `{}`", self.name),
+        }
+    }
+}
+
+/// The steps from a call site to the external call it leads to
+#[derive(Clone)]
+struct ExternalPath {
+    /// Name of the external call
+    call: String,
+    steps: Vec<AccessStep>,
+}
+
+/// The calls between the functions of a dot, and what they imply for external calls and guards
+struct CallGraph {
+    /// How each function that reaches an external call gets there
+    reaches_external: HashMap<String, ExternalPath>,
+    /// Functions that are only ever called after a guard
+    guarded: HashSet<String>,
+}
+
+impl CallGraph {
+    fn build(config: &ReentrancyConfig, functions: &[Function], sites: &[Vec<CallSite>]) -> Self {
+        // Functions sharing a name, such as methods of different types, share a node
+        let mut sites_by_caller: HashMap<&str, Vec<&CallSite>> = HashMap::new();
+        for (function, sites) in functions.iter().zip(sites) {
+            sites_by_caller.entry(function.name.as_str()).or_default().extend(sites);
+        }
+
+        let mut builder = GraphBuilder::new();
+        for (caller, sites) in &sites_by_caller {
+            builder.add_node(caller.to_string());
+            for site in sites.iter().filter(|site| sites_by_caller.contains_key(site.name.as_str())) {
+                builder.add_edge(caller, &site.name);
+            }
+        }
+        let graph = builder.build();
+        let nodes: HashMap<&str, NodeIndex> = graph.node_indices().map(|node| (graph[node].id.as_str(), node)).collect();
+
+        let reaches_external = Self::reaches_external(config, &graph, &nodes, &sites_by_caller);
+        let guarded = Self::guarded(config, &graph, &sites_by_caller);
+        Self { reaches_external, guarded }
+    }
+
+    /// The shortest call chain from each function to an external call, found backwards from
+    /// the functions making one
+    fn reaches_external(config: &ReentrancyConfig, graph: &DependencyGraph, nodes: &HashMap<&str, NodeIndex>, sites: &HashMap<&str, Vec<&CallSite>>) -> HashMap<String, ExternalPath> {
+        let mut reaches = HashMap::new();
+        let mut queue = VecDeque::new();
+        for (function, sites) in sites {
+            if let Some(site) = sites.iter().find(|site| named(&config.external_calls, &site.name)) {
+                let path = ExternalPath {
+                    call: site.name.clone(),
+                    steps: vec![site.step(function)],
+                };
+                reaches.insert(function.to_string(), path);
+                queue.push_back(*function);
+            }
+        }
+
+        while let Some(callee) = queue.pop_front() {
+            for caller in graph.neighbors_directed(nodes[callee], Direction::Incoming) {
+                let caller = graph[caller].id.as_str();
+                if reaches.contains_key(caller) {
+                    continue;
+                }
+                let Some(site) = sites[caller].iter().find(|site| site.name == callee) else {
+                    continue;
+                };
+                let mut path: ExternalPath = reaches[callee].clone();
+                path.steps.insert(0, site.step(caller));
+                reaches.insert(caller.to_string(), path);
+                queue.push_back(caller);
+            }
+        }
+        reaches
+    }
+
+    /// Functions whose every call site follows a guard, or is in a function that is itself
+    /// only called after one
+    fn guarded(config: &ReentrancyConfig, graph: &DependencyGraph, sites: &HashMap<&str, Vec<&CallSite>>) -> HashSet<String> {
+        let guard_at = |function: &str| sites[function].iter().filter(|site| named(&config.guards, &site.name)).map(|site| site.position).min();
+
+        let mut guarded = HashSet::new();
+        loop {
+            let mut changed = false;
+            for node in graph.node_indices() {
+                let callee = graph[node].id.as_str();
+                let mut callers = graph.neighbors_directed(node, Direction::Incoming).peekable();
+                if guarded.contains(callee) || callers.peek().is_none() {
+                    continue;
+                }
+                let protected = callers.all(|caller| {
+                    let caller = graph[caller].id.as_str();
+                    guarded.contains(caller)
+                        || sites[caller]
+                            .iter()
+                            .filter(|site| site.name == callee)
+                            .all(|site| guard_at(caller).is_some_and(|guard| guard < site.position))
+                });
+                if protected {
+                    guarded.insert(callee.to_string());
+                    changed = true;
+                }
+            }
+            if !changed {
+                return guarded;
+            }
+        }
+    }
+}
+
+/// A value read from state
+#[derive(Debug, Clone, PartialEq)]
+struct Read {
+    /// The state read, as the read call's first argument is written
+    key: String,
+    line: usize,
+    position: usize,
+}
+
+/// A call that leaves the dot, directly or through the functions it calls
+struct External {
+    position: usize,
+    path: ExternalPath,
+}
+
+/// One walk over a function body
+struct FunctionPass<'a> {
+    config: &'a ReentrancyConfig,
+    calls: &'a Regex,
+    function: &'a Function,
+    externals: Vec<External>,
+    guard: Option<usize>,
+    vars: HashMap<String, Vec<Read>>,
+}
+
+impl<'a> FunctionPass<'a> {
+    fn new(analyzer: &'a ReentrancyAnalyzer, function: &'a Function, sites: &[CallSite], calls: &CallGraph) -> Self {
+        let config = &analyzer.config;
+        let externals = sites
+            .iter()
+            .filter_map(|site| {
+                let mut path = if named(&config.external_calls, &site.name) {
+                    ExternalPath {
+                        call: site.name.clone(),
+                        steps: Vec::new(),
+                    }
+                } else {
+                    calls.reaches_external.get(&site.name)?.clone()
+                };
+                path.steps.insert(0, site.step(&function.name));
+                Some(External { position: site.position, path })
+            })
+            .collect();
+        let guard = sites.iter().filter(|site| named(&config.guards, &site.name)).map(|site| site.position).min();
+        Self {
+            config,
+            calls: &analyzer.calls,
+            function,
+            externals,
+            guard,
+            vars: HashMap::new(),
+        }
+    }
+
+    fn run(&mut self, body: &[Statement], conflicts: &mut Vec<StateConflict>) {
+        for statement in body {
+            self.writes(statement, conflicts);
+            self.assignment(statement);
+        }
+    }
+
+    /// Reports writes of a value read from the same state before an unguarded external call
+    fn writes(&self, statement: &Statement, conflicts: &mut Vec<StateConflict>) {
+        let code = statement.code;
+        for call in self.calls.captures_iter(code) {
+            if !named(&self.config.state_writes, &call[1]) {
+                continue;
+            }
+            let start = call.get(0).expect("a match has a whole").end();
+            let Some(close) = closing_paren(&code[start..]) else {
+                continue;
+            };
+            let arguments: Vec<_> = top_level_ranges(&code[start..start + close], ',', false)
+                .into_iter()
+                .map(|range| range.start + start..range.end + start)
+                .collect();
+            let [key, value, ..] = arguments.as_slice() else {
+                continue;
+            };
+            let key = statement.text_of(key.clone());
+            let written = write_position(statement.index);
+
+            let mut reads: Vec<_> = self.reads_of(statement, value.start..start + close).into_iter().filter(|read| read.key == key).collect();
+            reads.sort_by_key(|read| read.position);
+            let found = reads.iter().find_map(|read| {
+                let external = self.externals.iter().find(|external| read.position < external.position && external.position < written)?;
+                let guarded = self.guard.is_some_and(|guard| guard < external.position);
+                (!guarded).then_some((read, external))
+            });
+            if let Some((read, external)) = found {
+                conflicts.push(self.conflict(read, external, statement.line));
+            }
+        }
+    }
+
+    fn conflict(&self, read: &Read, external: &External, line: usize) -> StateConflict {
+        let state = read.key.trim_matches('"');
+        let mut path = vec![AccessStep {
+            function: self.function.name.clone(),
+            line: read.line,
+            description: format!("reads `{}`", state),
+        }];
+        path.extend(external.path.steps.iter().cloned());
+        path.push(AccessStep {
+            function: self.function.name.clone(),
+            line,
+            description: format!("writes `{}` from the value read on line {}", state, read.line),
+        });
+        StateConflict {
+            location: state.to_string(),
+            first_access: StateAccessType::Read,
+            second_access: StateAccessType::Write,
+            description: format!(
+                "`{}` writes `{}` from a value read before calling `{}`, which can re-enter the dot and change it in between",
+                self.function.name, state, external.path.call
+            ),
+            severity: ConflictSeverity::High,
+            path,
+        }
+    }
+
+    /// Applies `let`, plain and compound assignments
+    fn assignment(&mut self, statement: &Statement) {
+        let code = statement.code;
+        // An assignment in a nested block may not happen, so it only adds to what a variable holds
+        let strong = statement.depth == 0;
+        if let Some(rest) = code.strip_prefix("let ") {
+            let Some((at, len, _)) = assignment_operator(rest) else {
+                return;
+            };
+            let pattern = split_top_level(&rest[..at], ':', false).into_iter().next().unwrap_or_default();
+            let offset = code.len() - rest.len();
+            let reads = self.reads_of(statement, offset + at + len..code.len());
+            for name in pattern_names(&pattern) {
+                self.assign(&name, &reads, strong);
+            }
+            return;
+        }
+
+        let Some((at, len, compound)) = assignment_operator(code) else {
+            return;
+        };
+        let target = code[..at].trim();
+        let Some(base) = leading_ident(target) else {
+            return;
+        };
+        let reads = self.reads_of(statement, at + len..code.len());
+        // Writing through a field, index or reference keeps whatever else the value held
+        let whole = target == base;
+        self.assign(base, &reads, strong && whole && !compound);
+    }
+
+    fn assign(&mut self, name: &str, reads: &[Read], strong: bool) {
+        let held = self.vars.entry(name.to_string()).or_default();
+        if strong {
+            held.clear();
+        }
+        for read in reads {
+            if !held.contains(read) {
+                held.push(read.clone());
+            }
+        }
+    }
+
+    /// The state reads the expression at `range` of `statement` depends on
+    fn reads_of(&self, statement: &Statement, range: Range<usize>) -> Vec<Read> {
+        let code = statement.code;
+        let bytes = code.as_bytes();
+        let mut reads = Vec::new();
+        let mut at = range.start;
+        while at < range.end {
+            if !is_ident_start(bytes[at]) || (at > 0 && is_ident_byte(bytes[at - 1])) {
+                at += 1;
+                continue;
+            }
+            let start = at;
+            while at < range.end && is_ident_byte(bytes[at]) {
+                at += 1;
+            }
+            let name = &code[start..at];
+            let before = code[..start].trim_end();
+            let after = code[at..range.end].trim_start();
+            if before.ends_with('.') && !before.ends_with("..") {
+                // A field or method, already covered by the value it belongs to
+                continue;
+            }
+            if after.starts_with('(') {
+                let arguments = range.end - after.len() + 1;
+                if named(&self.config.state_reads, name)
+                    && let Some(close) = closing_paren(&code[arguments..range.end])
+                {
+                    let key = top_level_ranges(&code[arguments..arguments + close], ',', false)
+                        .first()
+                        .map(|key| statement.text_of(key.start + arguments..key.end + arguments).to_string());
+                    reads.extend(key.map(|key| Read {
+                        key,
+                        line: statement.line,
+                        position: read_position(statement.index),
+                    }));
+                    at = arguments + close + 1;
+                }
+                // Other calls depend on their arguments, which are read next
+                continue;
+            }
+            if let Some(held) = self.vars.get(name) {
+                reads.extend(held.iter().cloned());
+            }
+        }
+        reads
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Withdrawals written the vulnerable way and each of the ways that make them safe
+    const VAULT: &str = r#"fn withdraw(amount: u64) {
+    let balance = get_state("balance");
+    require(balance >= amount);
+    call_dot("payee", amount);
+    set_state("balance", balance - amount);
+}
+
+fn withdraw_checks_effects_interactions(amount: u64) {
+    let balance = get_state("balance");
+    require(balance >= amount);
+    set_state("balance", balance - amount);
+    call_dot("payee", amount);
+}
+
+fn withdraw_guarded(amount: u64) {
+    reentrancy_guard();
+    let balance = get_state("balance");
+    call_dot("payee", amount);
+    set_state("balance", balance - amount);
+}
+
+fn withdraw_rereading(amount: u64) {
+    let before = get_state("balance");
+    call_dot("payee", amount);
+    let balance = get_state("balance");
+    set_state("balance", balance - amount);
+    set_state("last_seen", before);
+}
+
+fn pay(to: Address, amount: u64) {
+    log("paying");
+    external_call(to, amount);
+}
+
+fn withdraw_through_helper(to: Address, amount: u64) {
+    let credit = get_state("credit");
+    let remaining = credit - amount;
+    pay(to, amount);
+    set_state("credit", remaining);
+}
+
+fn settle(amount: u64) {
+    non_reentrant();
+    settle_owed(amount);
+}
+
+fn settle_owed(amount: u64) {
+    let owed = get_state("owed");
+    pay(owner(), owed);
+    set_state("owed", owed - amount);
+}"#;
+
+    fn flagged(analyzer: &ReentrancyAnalyzer, source: &str) -> Vec<(String, usize)> {
+        analyzer
+            .find_reentrancy(source)
+            .iter()
+            .map(|conflict| {
+                let write = conflict.path.last().expect("a conflict ends with its write");
+                (write.function.clone(), write.line)
+            })
+            .collect()
+    }
+
+    fn steps(conflict: &StateConflict) -> Vec<(&str, usize, &str)> {
+        conflict.path.iter().map(|step| (step.function.as_str(), step.line, step.description.as_str())).collect()
+    }
+
+    #[test]
+    fn test_only_unguarded_read_call_write_sequences_are_flagged() {
+        let flagged = flagged(&ReentrancyAnalyzer::default(), VAULT);
+        assert_eq!(flagged, [("withdraw".to_string(), 5), ("withdraw_through_helper".to_string(), 39)]);
+    }
+
+    #[test]
+    fn test_conflict_reports_the_path_through_called_functions() {
+        let conflicts = ReentrancyAnalyzer::default().find_reentrancy(VAULT);
+        let conflict = &conflicts[1];
+
+        assert_eq!(conflict.location, "credit");
+        assert_eq!((conflict.first_access.clone(), conflict.second_access.clone()), (StateAccessType::Read, StateAccessType::Write));
+        assert_eq!(conflict.severity, ConflictSeverity::High);
+        assert_eq!(
+            conflict.description,
+            "`withdraw_through_helper` writes `credit` from a value read before calling `external_call`, which can re-enter the dot and change it in between"
+        );
+        assert_eq!(
+            steps(conflict),
+            [
+                ("withdraw_through_helper", 36, "reads `credit`"),
+                ("withdraw_through_helper", 38, "calls `pay`"),
+                ("pay", 32, "calls `external_call`"),
+                ("withdraw_through_helper", 39, "writes `credit` from the value read on line 36")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_function_called_from_an_unguarded_site_is_flagged() {
+        let source = format!("{VAULT}\n\nfn refund() {{\n    settle_owed(1);\n}}");
+        let flagged = flagged(&ReentrancyAnalyzer::default(), &source);
+        assert_eq!(flagged.last(), Some(&("settle_owed".to_string(), 50)));
+        assert_eq!(flagged.len(), 3);
+    }
+
+    #[test]
+    fn test_configured_guard_names() {
+        let source = r#"impl Vault {
+    fn withdraw(&self, amount: u64) {
+        lock_vault();
+        let mut balance = get_state("balance");
+        balance -= amount;
+        self.call_dot("payee", amount);
+        set_state("balance", balance);
+    }
+}"#;
+
+        assert_eq!(flagged(&ReentrancyAnalyzer::default(), source), [("withdraw".to_string(), 7)]);
+        let analyzer = ReentrancyAnalyzer::new(ReentrancyConfig::default().with_guards(["lock_vault"]));
+        assert!(analyzer.analyze(source).unwrap().is_empty());
+        assert_eq!(analyzer.name(), "reentrancy");
+    }
+}
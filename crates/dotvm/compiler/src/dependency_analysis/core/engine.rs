@@ -17,8 +17,9 @@
 //! Dependency analysis engine - legacy components removed
 
 use crate::dependency_analysis::{
+    analyzers::control_flow::{ControlFlowGraph, ControlFlowLoop, IrreducibleRegion},
     config::EngineConfig,
-    detection::{DependencyDetector, DependencyInfo, DetectorRegistry},
+    detection::{DependencyInfo, DetectorRegistry},
 };
 use std::collections::HashMap;
 
@@ -83,12 +84,36 @@ pub struct ControlFlowAnalysis {
     pub nodes: Vec<String>,
     pub edges: Vec<String>,
     pub complexity: ComplexityMetrics,
+    /// Natural loops, outer loops before the loops they contain
+    pub loops: Vec<ControlFlowLoop>,
+    /// Cycles with several entries, which are not reported as loops
+    pub irreducible_regions: Vec<IrreducibleRegion>,
+}
+
+impl ControlFlowAnalysis {
+    /// Summarise an analysed control flow graph
+    pub fn from_graph(cfg: &ControlFlowGraph) -> Self {
+        let mut nodes: Vec<usize> = cfg.nodes.keys().copied().collect();
+        nodes.sort_unstable();
+        Self {
+            nodes: nodes.iter().map(usize::to_string).collect(),
+            edges: cfg.edges.iter().map(|e| format!("{}->{}", e.from, e.to)).collect(),
+            complexity: ComplexityMetrics {
+                cyclomatic: cfg.complexity.cyclomatic,
+                max_loop_depth: cfg.complexity.max_loop_depth,
+            },
+            loops: cfg.loops().to_vec(),
+            irreducible_regions: cfg.irreducible_regions.clone(),
+        }
+    }
 }
 
 /// Complexity metrics
 #[derive(Debug, Clone)]
 pub struct ComplexityMetrics {
     pub cyclomatic: usize,
+    /// Deepest loop nesting; 0 without loops
+    pub max_loop_depth: usize,
 }
 
 /// Complete analysis result from the dependency analysis engine
@@ -157,7 +182,9 @@ impl DependencyAnalysisEngine {
             control_flow: Some(ControlFlowAnalysis {
                 nodes: vec!["entry".to_string(), "exit".to_string()],
                 edges: vec!["entry->exit".to_string()],
-                complexity: ComplexityMetrics { cyclomatic: 2 }, // Sample complexity for tests
+                complexity: ComplexityMetrics { cyclomatic: 2, max_loop_depth: 0 }, // Sample complexity for tests
+                loops: Vec::new(),
+                irreducible_regions: Vec::new(),
             }),
         };
 
@@ -219,4 +246,21 @@ mod tests {
         let analysis_result = result.unwrap();
         assert_eq!(analysis_result.dependencies.len(), 0); // No dependencies detected in simple test
     }
+
+    #[test]
+    fn test_control_flow_analysis_from_graph() {
+        use crate::dependency_analysis::analyzers::control_flow::{ControlFlowGraphBuilder, ControlFlowNode, Terminator};
+
+        let cfg = ControlFlowGraphBuilder::build(vec![
+            ControlFlowNode::new(0, Terminator::FallThrough),
+            ControlFlowNode::new(1, Terminator::Branch { target: 0, condition: None }),
+            ControlFlowNode::new(2, Terminator::Return),
+        ])
+        .unwrap();
+        let analysis = ControlFlowAnalysis::from_graph(&cfg);
+        assert_eq!(analysis.edges, ["0->1", "1->0", "1->2"]);
+        assert_eq!((analysis.complexity.cyclomatic, analysis.complexity.max_loop_depth), (2, 1));
+        assert_eq!(analysis.loops[0].body, [0, 1]);
+        assert!(analysis.irreducible_regions.is_empty());
+    }
 }
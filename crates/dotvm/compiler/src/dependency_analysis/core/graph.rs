@@ -39,6 +39,12 @@ pub struct GraphBuilder {
     indices: std::collections::HashMap<String, NodeIndex>,
 }
 
+impl Default for GraphBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl GraphBuilder {
     /// Create a new graph builder
     pub fn new() -> Self {
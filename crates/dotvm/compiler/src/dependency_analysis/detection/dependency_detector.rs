@@ -178,15 +178,11 @@ impl BasicDependencyDetector {
             return None;
         }
 
-        if let Some(column) = line.find(pattern) {
-            Some(SourceLocation {
-                line: line_num,
-                column: column + 1, // 1-based
-                length: pattern.len(),
-            })
-        } else {
-            None
-        }
+        line.find(pattern).map(|column| SourceLocation {
+            line: line_num,
+            column: column + 1, // 1-based
+            length: pattern.len(),
+        })
     }
 }
 
@@ -206,21 +202,21 @@ impl Detector for BasicDependencyDetector {
 
             // Check each pattern
             for (pattern, dep_type) in &self.patterns {
-                if line.contains(pattern) {
-                    if let Some(name) = self.extract_dependency_name(line, pattern) {
-                        let location = self.create_location(line_num + 1, original_line, pattern);
-
-                        let mut metadata = HashMap::new();
-                        metadata.insert("pattern".to_string(), pattern.clone());
-                        metadata.insert("source_line".to_string(), original_line.to_string());
-
-                        dependencies.push(DependencyInfo {
-                            name,
-                            dependency_type: dep_type.clone(),
-                            source_location: location,
-                            metadata,
-                        });
-                    }
+                if line.contains(pattern)
+                    && let Some(name) = self.extract_dependency_name(line, pattern)
+                {
+                    let location = self.create_location(line_num + 1, original_line, pattern);
+
+                    let mut metadata = HashMap::new();
+                    metadata.insert("pattern".to_string(), pattern.clone());
+                    metadata.insert("source_line".to_string(), original_line.to_string());
+
+                    dependencies.push(DependencyInfo {
+                        name,
+                        dependency_type: dep_type.clone(),
+                        source_location: location,
+                        metadata,
+                    });
                 }
             }
         }
@@ -259,14 +255,12 @@ pub struct RegexDependencyDetector {
 impl RegexDependencyDetector {
     /// Create a new regex dependency detector
     pub fn new() -> Result<Self, regex::Error> {
-        let mut regex_patterns = Vec::new();
-
-        // Add common regex patterns
-        regex_patterns.push((regex::Regex::new(r"import\s+([a-zA-Z_][a-zA-Z0-9_]*)")?, DependencyType::Module));
-
-        regex_patterns.push((regex::Regex::new(r#"require\(["']([^"']+)["']\)"#)?, DependencyType::Module));
-
-        regex_patterns.push((regex::Regex::new(r"([a-zA-Z_][a-zA-Z0-9_]*)\s*\(")?, DependencyType::Function));
+        // Common regex patterns
+        let regex_patterns = vec![
+            (regex::Regex::new(r"import\s+([a-zA-Z_][a-zA-Z0-9_]*)")?, DependencyType::Module),
+            (regex::Regex::new(r#"require\(["']([^"']+)["']\)"#)?, DependencyType::Module),
+            (regex::Regex::new(r"([a-zA-Z_][a-zA-Z0-9_]*)\s*\(")?, DependencyType::Function),
+        ];
 
         Ok(Self {
             regex_patterns,
@@ -17,7 +17,7 @@
 //! Graph pattern matcher
 
 use crate::dependency_analysis::core::traits::PatternMatcher;
-use crate::dependency_analysis::detection::{MatchResult, Pattern};
+use crate::dependency_analysis::detection::Pattern;
 
 /// Matches graph-shaped patterns
 pub struct GraphMatcher;
@@ -35,8 +35,7 @@ impl PatternMatcher for GraphMatcher {
         0.5
     }
 
-    fn extract_info(&self, _pattern: &Self::Pattern, _context: &Self::Context) -> () {
+    fn extract_info(&self, _pattern: &Self::Pattern, _context: &Self::Context) {
         // Extract information when a match is found
-        ()
     }
 }
@@ -17,7 +17,7 @@
 //! Heuristic pattern matcher for advanced matching
 
 use crate::dependency_analysis::core::traits::PatternMatcher;
-use crate::dependency_analysis::detection::{MatchResult, Pattern};
+use crate::dependency_analysis::detection::Pattern;
 
 /// Applies heuristic matching strategies
 pub struct HeuristicMatcher;
@@ -35,8 +35,7 @@ impl PatternMatcher for HeuristicMatcher {
         0.3
     }
 
-    fn extract_info(&self, _pattern: &Self::Pattern, _context: &Self::Context) -> () {
+    fn extract_info(&self, _pattern: &Self::Pattern, _context: &Self::Context) {
         // Extract information when a match is found
-        ()
     }
 }
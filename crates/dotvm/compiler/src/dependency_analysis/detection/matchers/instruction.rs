@@ -17,7 +17,7 @@
 //! Instruction pattern matcher
 
 use crate::dependency_analysis::core::traits::PatternMatcher;
-use crate::dependency_analysis::detection::{MatchResult, Pattern};
+use crate::dependency_analysis::detection::Pattern;
 
 /// Matches individual instructions against patterns
 pub struct InstructionMatcher;
@@ -27,15 +27,14 @@ impl PatternMatcher for InstructionMatcher {
     type Context = String;
 
     fn matches(&self, pattern: &Self::Pattern, context: &Self::Context) -> bool {
-        pattern.pattern_type == pattern.pattern_type && context.contains(&pattern.pattern)
+        context.contains(&pattern.pattern)
     }
 
     fn confidence(&self) -> f32 {
         1.0
     }
 
-    fn extract_info(&self, _pattern: &Self::Pattern, _context: &Self::Context) -> () {
+    fn extract_info(&self, _pattern: &Self::Pattern, _context: &Self::Context) {
         // Extract information when a match is found
-        ()
     }
 }
@@ -17,7 +17,7 @@
 //! Sequence pattern matcher
 
 use crate::dependency_analysis::core::traits::PatternMatcher;
-use crate::dependency_analysis::detection::{MatchResult, Pattern};
+use crate::dependency_analysis::detection::Pattern;
 
 /// Matches a sequence of instructions
 pub struct SequenceMatcher;
@@ -35,8 +35,7 @@ impl PatternMatcher for SequenceMatcher {
         0.8
     }
 
-    fn extract_info(&self, _pattern: &Self::Pattern, _context: &Self::Context) -> () {
+    fn extract_info(&self, _pattern: &Self::Pattern, _context: &Self::Context) {
         // Extract information when a match is found
-        ()
     }
 }
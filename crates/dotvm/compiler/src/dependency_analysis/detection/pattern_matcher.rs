@@ -16,6 +16,8 @@
 
 //! Pattern matching utilities for dependency detection
 
+use aho_corasick::AhoCorasick;
+use regex::{Regex, RegexSet};
 use std::collections::HashMap;
 
 /// Types of patterns that can be matched
@@ -154,12 +156,18 @@ pub struct MatchResult {
 }
 
 /// Pattern matcher for finding patterns in text
+///
+/// Patterns are compiled before matching so that a search makes one pass over the text
+/// whatever the number of patterns: literal patterns (exact, prefix, suffix and contains)
+/// share an Aho-Corasick automaton, and regex and wildcard patterns are tested together as
+/// a regex set. Compilation happens on the first search after patterns change, or
+/// explicitly through [`compile`](Self::compile).
 #[derive(Debug)]
 pub struct PatternMatcher {
     /// Registered patterns
     patterns: Vec<Pattern>,
-    /// Compiled regex patterns (cached)
-    regex_cache: HashMap<String, regex::Regex>,
+    /// Patterns compiled for matching, `None` until compiled or after patterns change
+    compiled: Option<CompiledPatterns>,
     /// Maximum number of matches to return
     max_matches: usize,
     /// Whether to return overlapping matches
@@ -170,7 +178,7 @@ impl Default for PatternMatcher {
     fn default() -> Self {
         Self {
             patterns: Vec::new(),
-            regex_cache: HashMap::new(),
+            compiled: None,
             max_matches: 1000,
             allow_overlapping: false,
         }
@@ -199,13 +207,14 @@ impl PatternMatcher {
     pub fn add_pattern(&mut self, pattern: Pattern) {
         self.patterns.push(pattern);
         // Sort by priority (highest first)
-        self.patterns.sort_by(|a, b| b.priority.cmp(&a.priority));
+        self.patterns.sort_by_key(|pattern| std::cmp::Reverse(pattern.priority));
+        self.compiled = None;
     }
 
     /// Remove all patterns
     pub fn clear_patterns(&mut self) {
         self.patterns.clear();
-        self.regex_cache.clear();
+        self.compiled = None;
     }
 
     /// Get all registered patterns
@@ -213,15 +222,30 @@ impl PatternMatcher {
         &self.patterns
     }
 
+    /// Whether the current patterns are compiled
+    pub fn is_compiled(&self) -> bool {
+        self.compiled.is_some()
+    }
+
+    /// Compile the registered patterns unless they already are
+    ///
+    /// Regex and wildcard patterns that are not valid regular expressions never match.
+    pub fn compile(&mut self) {
+        self.compiled.get_or_insert_with(|| CompiledPatterns::new(&self.patterns));
+    }
+
     /// Find all matches in the given text
+    ///
+    /// Patterns claim matches in priority order: unless overlapping matches are allowed,
+    /// a match overlapping one claimed by an earlier pattern is dropped. The matches are
+    /// returned ordered by start position.
     pub fn find_matches(&mut self, text: &str) -> Vec<MatchResult> {
+        let compiled = self.compiled.get_or_insert_with(|| CompiledPatterns::new(&self.patterns));
+
         let mut matches = Vec::new();
         let mut used_positions = std::collections::HashSet::new();
 
-        let patterns = self.patterns.clone(); // Clone to avoid borrow checker issues
-        for pattern in &patterns {
-            let pattern_matches = self.find_pattern_matches(text, pattern);
-
+        for pattern_matches in compiled.find_candidates(&self.patterns, text) {
             for match_result in pattern_matches {
                 // Check for overlapping matches if not allowed
                 if !self.allow_overlapping {
@@ -254,151 +278,188 @@ impl PatternMatcher {
         matches.sort_by_key(|m| m.start_position);
         matches
     }
+}
 
-    /// Find matches for a specific pattern
-    fn find_pattern_matches(&mut self, text: &str, pattern: &Pattern) -> Vec<MatchResult> {
-        let search_text = if pattern.case_sensitive { text.to_string() } else { text.to_lowercase() };
-
-        let search_pattern = if pattern.case_sensitive { pattern.pattern.clone() } else { pattern.pattern.to_lowercase() };
+/// Patterns compiled for single-pass matching
+///
+/// Case-insensitive patterns are lowercased and matched against the lowercased text, so
+/// their positions refer to the lowercased text. Regex patterns always match the text as
+/// given.
+#[derive(Debug)]
+struct CompiledPatterns {
+    /// Case-sensitive literal patterns
+    literals: LiteralSet,
+    /// Case-insensitive literal patterns
+    lowercase_literals: LiteralSet,
+    /// Regex patterns
+    regexes: RegexGroup,
+    /// Case-sensitive wildcard patterns, anchored to the whole text
+    wildcards: RegexGroup,
+    /// Case-insensitive wildcard patterns, anchored to the whole text
+    lowercase_wildcards: RegexGroup,
+}
 
-        match pattern.pattern_type {
-            PatternType::Exact => self.find_exact_matches(&search_text, &search_pattern, pattern),
-            PatternType::Prefix => self.find_prefix_matches(&search_text, &search_pattern, pattern),
-            PatternType::Suffix => self.find_suffix_matches(&search_text, &search_pattern, pattern),
-            PatternType::Contains => self.find_contains_matches(&search_text, &search_pattern, pattern),
-            PatternType::Regex => self.find_regex_matches(text, pattern),
-            PatternType::Wildcard => self.find_wildcard_matches(&search_text, &search_pattern, pattern),
+impl CompiledPatterns {
+    fn new(patterns: &[Pattern]) -> Self {
+        let mut literals = Vec::new();
+        let mut lowercase_literals = Vec::new();
+        let mut regexes = Vec::new();
+        let mut wildcards = Vec::new();
+        let mut lowercase_wildcards = Vec::new();
+
+        for (index, pattern) in patterns.iter().enumerate() {
+            let search_pattern = if pattern.case_sensitive { pattern.pattern.clone() } else { pattern.pattern.to_lowercase() };
+            match pattern.pattern_type {
+                PatternType::Exact | PatternType::Prefix | PatternType::Suffix | PatternType::Contains => {
+                    let group = if pattern.case_sensitive { &mut literals } else { &mut lowercase_literals };
+                    group.push((index, search_pattern));
+                }
+                PatternType::Regex => regexes.push((index, pattern.pattern.clone())),
+                PatternType::Wildcard => {
+                    let group = if pattern.case_sensitive { &mut wildcards } else { &mut lowercase_wildcards };
+                    group.push((index, format!("^{}$", search_pattern.replace("*", ".*").replace("?", "."))));
+                }
+            }
         }
-    }
 
-    /// Find exact matches
-    fn find_exact_matches(&self, text: &str, pattern: &str, original_pattern: &Pattern) -> Vec<MatchResult> {
-        if text == pattern {
-            vec![MatchResult {
-                pattern: original_pattern.clone(),
-                matched_text: text.to_string(),
-                start_position: 0,
-                end_position: text.len(),
-                captured_groups: Vec::new(),
-                score: 1.0,
-            }]
-        } else {
-            Vec::new()
+        Self {
+            literals: LiteralSet::new(literals),
+            lowercase_literals: LiteralSet::new(lowercase_literals),
+            regexes: RegexGroup::new(regexes),
+            wildcards: RegexGroup::new(wildcards),
+            lowercase_wildcards: RegexGroup::new(lowercase_wildcards),
         }
     }
 
-    /// Find prefix matches
-    fn find_prefix_matches(&self, text: &str, pattern: &str, original_pattern: &Pattern) -> Vec<MatchResult> {
-        if text.starts_with(pattern) {
-            vec![MatchResult {
-                pattern: original_pattern.clone(),
-                matched_text: pattern.to_string(),
-                start_position: 0,
-                end_position: pattern.len(),
-                captured_groups: Vec::new(),
-                score: pattern.len() as f64 / text.len() as f64,
-            }]
-        } else {
-            Vec::new()
+    /// Every match of every pattern, grouped by pattern index and ordered by start position
+    fn find_candidates(&self, patterns: &[Pattern], text: &str) -> Vec<Vec<MatchResult>> {
+        let mut candidates = vec![Vec::new(); patterns.len()];
+
+        self.literals.find(patterns, text, &mut candidates);
+        self.wildcards.find_whole(patterns, text, &mut candidates);
+        if !self.lowercase_literals.is_empty() || !self.lowercase_wildcards.is_empty() {
+            let lowercase_text = text.to_lowercase();
+            self.lowercase_literals.find(patterns, &lowercase_text, &mut candidates);
+            self.lowercase_wildcards.find_whole(patterns, &lowercase_text, &mut candidates);
         }
-    }
 
-    /// Find suffix matches
-    fn find_suffix_matches(&self, text: &str, pattern: &str, original_pattern: &Pattern) -> Vec<MatchResult> {
-        if text.ends_with(pattern) {
-            let start_pos = text.len() - pattern.len();
-            vec![MatchResult {
-                pattern: original_pattern.clone(),
-                matched_text: pattern.to_string(),
-                start_position: start_pos,
-                end_position: text.len(),
-                captured_groups: Vec::new(),
-                score: pattern.len() as f64 / text.len() as f64,
-            }]
-        } else {
-            Vec::new()
+        for (member, regex) in self.regexes.matching(text) {
+            let pattern = &patterns[self.regexes.pattern_ids[member]];
+            for capture in regex.captures_iter(text) {
+                if let Some(full_match) = capture.get(0) {
+                    let captured_groups: Vec<String> = capture.iter().skip(1).filter_map(|m| m.map(|m| m.as_str().to_string())).collect();
+
+                    candidates[self.regexes.pattern_ids[member]].push(MatchResult {
+                        pattern: pattern.clone(),
+                        matched_text: full_match.as_str().to_string(),
+                        start_position: full_match.start(),
+                        end_position: full_match.end(),
+                        captured_groups,
+                        score: full_match.len() as f64 / text.len() as f64,
+                    });
+                }
+            }
         }
+
+        candidates
     }
+}
 
-    /// Find contains matches
-    fn find_contains_matches(&self, text: &str, pattern: &str, original_pattern: &Pattern) -> Vec<MatchResult> {
-        let mut matches = Vec::new();
-        let mut start = 0;
-
-        while let Some(pos) = text[start..].find(pattern) {
-            let absolute_pos = start + pos;
-            matches.push(MatchResult {
-                pattern: original_pattern.clone(),
-                matched_text: pattern.to_string(),
-                start_position: absolute_pos,
-                end_position: absolute_pos + pattern.len(),
-                captured_groups: Vec::new(),
-                score: pattern.len() as f64 / text.len() as f64,
-            });
+/// Literal patterns sharing one Aho-Corasick automaton
+#[derive(Debug)]
+struct LiteralSet {
+    automaton: Option<AhoCorasick>,
+    /// Pattern index of each automaton pattern
+    pattern_ids: Vec<usize>,
+}
 
-            start = absolute_pos + 1;
-        }
+impl LiteralSet {
+    fn new(literals: Vec<(usize, String)>) -> Self {
+        let (pattern_ids, needles): (Vec<usize>, Vec<String>) = literals.into_iter().unzip();
+        // Overlapping searches need standard match semantics; building only fails for
+        // automata far larger than any pattern library
+        let automaton = (!needles.is_empty()).then(|| AhoCorasick::new(&needles).expect("literal patterns fit in an Aho-Corasick automaton"));
+        Self { automaton, pattern_ids }
+    }
 
-        matches
+    fn is_empty(&self) -> bool {
+        self.automaton.is_none()
     }
 
-    /// Find regex matches
-    fn find_regex_matches(&mut self, text: &str, pattern: &Pattern) -> Vec<MatchResult> {
-        let regex = match self.get_or_compile_regex(&pattern.pattern) {
-            Ok(regex) => regex,
-            Err(_) => return Vec::new(),
+    /// Report every occurrence that satisfies its pattern's type
+    fn find(&self, patterns: &[Pattern], text: &str, candidates: &mut [Vec<MatchResult>]) {
+        let Some(automaton) = &self.automaton else {
+            return;
         };
 
-        let mut matches = Vec::new();
-        for capture in regex.captures_iter(text) {
-            if let Some(full_match) = capture.get(0) {
-                let captured_groups: Vec<String> = capture.iter().skip(1).filter_map(|m| m.map(|m| m.as_str().to_string())).collect();
-
-                matches.push(MatchResult {
-                    pattern: pattern.clone(),
-                    matched_text: full_match.as_str().to_string(),
-                    start_position: full_match.start(),
-                    end_position: full_match.end(),
-                    captured_groups,
-                    score: full_match.len() as f64 / text.len() as f64,
-                });
-            }
+        // Occurrences come ordered by end position, which orders each pattern's own
+        // occurrences by start position
+        for occurrence in automaton.find_overlapping_iter(text) {
+            let index = self.pattern_ids[occurrence.pattern().as_usize()];
+            let pattern = &patterns[index];
+            let (start, end) = (occurrence.start(), occurrence.end());
+            let score = match pattern.pattern_type {
+                PatternType::Exact if start == 0 && end == text.len() => 1.0,
+                PatternType::Prefix if start == 0 => (end - start) as f64 / text.len() as f64,
+                PatternType::Suffix if end == text.len() => (end - start) as f64 / text.len() as f64,
+                PatternType::Contains => (end - start) as f64 / text.len() as f64,
+                _ => continue,
+            };
+
+            candidates[index].push(MatchResult {
+                pattern: pattern.clone(),
+                matched_text: text[start..end].to_string(),
+                start_position: start,
+                end_position: end,
+                captured_groups: Vec::new(),
+                score,
+            });
         }
+    }
+}
 
-        matches
+/// Regular expressions tested together as a regex set
+#[derive(Debug)]
+struct RegexGroup {
+    /// `None` if the set could not be built, in which case each regex is tried in turn
+    set: Option<RegexSet>,
+    regexes: Vec<Regex>,
+    /// Pattern index of each regex
+    pattern_ids: Vec<usize>,
+}
+
+impl RegexGroup {
+    fn new(sources: Vec<(usize, String)>) -> Self {
+        let (pattern_ids, regexes): (Vec<usize>, Vec<Regex>) = sources.into_iter().filter_map(|(index, source)| Regex::new(&source).ok().map(|regex| (index, regex))).unzip();
+        let set = (!regexes.is_empty()).then(|| RegexSet::new(regexes.iter().map(Regex::as_str)).ok()).flatten();
+        Self { set, regexes, pattern_ids }
     }
 
-    /// Find wildcard matches
-    fn find_wildcard_matches(&self, text: &str, pattern: &str, original_pattern: &Pattern) -> Vec<MatchResult> {
-        // Convert wildcard pattern to regex
-        let regex_pattern = pattern.replace("*", ".*").replace("?", ".");
+    fn is_empty(&self) -> bool {
+        self.regexes.is_empty()
+    }
 
-        let regex = match regex::Regex::new(&format!("^{}$", regex_pattern)) {
-            Ok(regex) => regex,
-            Err(_) => return Vec::new(),
-        };
+    /// Regexes matching somewhere in `text`, with their position in the group
+    fn matching<'a>(&'a self, text: &str) -> Vec<(usize, &'a Regex)> {
+        match &self.set {
+            Some(set) => set.matches(text).into_iter().map(|member| (member, &self.regexes[member])).collect(),
+            None => self.regexes.iter().enumerate().filter(|(_, regex)| regex.is_match(text)).collect(),
+        }
+    }
 
-        if regex.is_match(text) {
-            vec![MatchResult {
-                pattern: original_pattern.clone(),
+    /// Report the whole text for each matching regex, which must be anchored to it
+    fn find_whole(&self, patterns: &[Pattern], text: &str, candidates: &mut [Vec<MatchResult>]) {
+        for (member, _) in self.matching(text) {
+            let index = self.pattern_ids[member];
+            candidates[index].push(MatchResult {
+                pattern: patterns[index].clone(),
                 matched_text: text.to_string(),
                 start_position: 0,
                 end_position: text.len(),
                 captured_groups: Vec::new(),
                 score: 1.0,
-            }]
-        } else {
-            Vec::new()
-        }
-    }
-
-    /// Get or compile a regex pattern
-    fn get_or_compile_regex(&mut self, pattern: &str) -> Result<&regex::Regex, regex::Error> {
-        if !self.regex_cache.contains_key(pattern) {
-            let regex = regex::Regex::new(pattern)?;
-            self.regex_cache.insert(pattern.to_string(), regex);
+            });
         }
-        Ok(self.regex_cache.get(pattern).unwrap())
     }
 }
 
@@ -523,4 +584,36 @@ mod tests {
         let matches = matcher.find_matches("aaaa");
         assert_eq!(matches.len(), 2);
     }
+
+    #[test]
+    fn test_suffix_match() {
+        let mut matcher = PatternMatcher::new();
+        matcher.add_pattern(Pattern::suffix(".wasm".to_string()));
+
+        let matches = matcher.find_matches("module.wasm");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].start_position, 6);
+        assert_eq!(matches[0].end_position, 11);
+        assert!(matcher.find_matches("module.wasm.bak").is_empty());
+    }
+
+    #[test]
+    fn test_compile_is_lazy_and_invalidated() {
+        let mut matcher = PatternMatcher::new();
+        matcher.add_pattern(Pattern::contains("import".to_string()));
+        assert!(!matcher.is_compiled());
+
+        assert_eq!(matcher.find_matches("import env").len(), 1);
+        assert!(matcher.is_compiled());
+
+        matcher.add_pattern(Pattern::regex(r"env\b".to_string()));
+        assert!(!matcher.is_compiled());
+        matcher.compile();
+        assert!(matcher.is_compiled());
+        assert_eq!(matcher.find_matches("import env").len(), 2);
+
+        matcher.clear_patterns();
+        assert!(!matcher.is_compiled());
+        assert!(matcher.find_matches("import env").is_empty());
+    }
 }
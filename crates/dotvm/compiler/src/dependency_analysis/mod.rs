@@ -1 +1,26 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
 
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Dependency analysis of transpiled dot code
+//!
+//! Analyzers for control flow, data flow and state access, detectors for dependency
+//! patterns, and the engine, configuration and reporting that tie them together.
+
+pub mod analyzers;
+pub mod config;
+pub mod core;
+pub mod detection;
+pub mod reporting;
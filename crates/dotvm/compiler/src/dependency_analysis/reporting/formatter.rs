@@ -16,7 +16,7 @@
 
 //! Formatting of analysis reports
 
-use crate::dependency_analysis::reporting::{AnalysisReport, ReportFormat};
+use crate::dependency_analysis::reporting::ReportFormat;
 
 /// Error during formatting
 #[derive(Debug)]
@@ -130,6 +130,7 @@ pub mod formatter;
 pub mod metrics;
 pub mod visualization;
 
-pub use formatter::{AnalysisReport, FormatError, ReportFormat, ReportFormatter};
+pub use crate::dependency_analysis::config::reporting::ReportFormat;
+pub use formatter::{AnalysisReport, FormatError, ReportFormatter};
 pub use metrics::AnalysisMetrics;
 pub use visualization::DependencyVisualizer;
@@ -43,4 +43,12 @@ pub struct OptimizationMetrics {
     pub pass_metrics: Vec<PassMetrics>,
     /// Total number of passes executed
     pub total_passes: usize,
+    /// Functions removed from the module
+    pub functions_removed: usize,
+    /// Estimated code size removed, in bytes
+    pub bytes_removed: usize,
+    /// Call sites replaced by the body of their callee
+    pub call_sites_inlined: usize,
+    /// Estimated code size of the inlined bodies, in bytes
+    pub bytes_added: usize,
 }
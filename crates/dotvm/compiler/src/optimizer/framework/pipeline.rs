@@ -21,6 +21,8 @@ use crate::optimizer::framework::metrics::{OptimizationMetrics, PassMetrics};
 use crate::optimizer::framework::pass::{OptimizationPass, OptimizationResult};
 use crate::optimizer::framework::scheduler::{DependencyResolver, ExecutionStrategy, ParallelizationHints, PassScheduler};
 use crate::optimizer::passes::constant_folding::{ConstantFolder, FoldingStats};
+use crate::optimizer::passes::inlining::InlineConfig;
+use crate::optimizer::passes::peephole::PeepholeConfig;
 use crate::transpiler::types::TranspiledFunction;
 use dotvm_core::bytecode::VmArchitecture;
 
@@ -28,12 +30,20 @@ use dotvm_core::bytecode::VmArchitecture;
 pub struct OptimizationConfig {
     pub target_arch: VmArchitecture,
     pub optimization_level: u8,
+    /// Functions that must survive dead code elimination, e.g. because they are invoked reflectively
+    pub keep: Vec<String>,
+    /// Which peephole rules run and how many passes they get per function
+    pub peephole: PeepholeConfig,
+    /// Size limits for function inlining
+    pub inline: InlineConfig,
 }
 
 /// Trait for collecting pipeline-level metrics
 pub trait MetricsCollector {
     /// Record metrics for a completed pass
     fn record_pass(&mut self, metrics: PassMetrics);
+    /// Accumulate the totals reported by a module-level pass
+    fn merge(&mut self, metrics: &OptimizationMetrics);
     /// Retrieve collected metrics
     fn collect(&self) -> &OptimizationMetrics;
 }
@@ -57,6 +67,12 @@ impl MetricsCollector for NoopMetricsCollector {
         self.metrics.pass_metrics.push(m);
         self.metrics.total_passes += 1;
     }
+    fn merge(&mut self, metrics: &OptimizationMetrics) {
+        self.metrics.functions_removed += metrics.functions_removed;
+        self.metrics.bytes_removed += metrics.bytes_removed;
+        self.metrics.call_sites_inlined += metrics.call_sites_inlined;
+        self.metrics.bytes_added += metrics.bytes_added;
+    }
     fn collect(&self) -> &OptimizationMetrics {
         &self.metrics
     }
@@ -119,6 +135,26 @@ impl OptimizationPipeline {
         current
     }
 
+    /// Record the outcome of a pass that ran outside the per-function pipeline
+    pub fn record_result<T>(&mut self, pass_name: &str, result: &OptimizationResult<T>) {
+        self.metrics.record_pass(PassMetrics {
+            pass_name: pass_name.to_string(),
+            duration_ms: 0,
+            changed: result.changed,
+        });
+        self.metrics.merge(&result.metrics);
+    }
+
+    /// Pipeline configuration
+    pub fn config(&self) -> &OptimizationConfig {
+        &self.config
+    }
+
+    /// Mutable pipeline configuration
+    pub fn config_mut(&mut self) -> &mut OptimizationConfig {
+        &mut self.config
+    }
+
     /// Retrieve pipeline-level metrics
     pub fn metrics(&self) -> &OptimizationMetrics {
         self.metrics.collect()
@@ -16,9 +16,32 @@
 
 //! Pass scheduling components
 
-/// Stub for a dependency resolver
+/// Orders passes so that each runs after the passes it depends on
 pub struct DependencyResolver;
 
+impl DependencyResolver {
+    /// Order `passes`, given as `(name, dependencies)`, returning indices into the slice
+    ///
+    /// Passes keep their relative order unless a dependency forces one later. Dependencies
+    /// on passes that are not in the slice are ignored, and passes caught in a dependency
+    /// cycle run last, in their original order.
+    pub fn order(&self, passes: &[(&str, &[&str])]) -> Vec<usize> {
+        let mut ordered = Vec::with_capacity(passes.len());
+        let mut placed = vec![false; passes.len()];
+        let is_pending = |name: &str, placed: &[bool]| passes.iter().zip(placed).any(|((pass, _), &done)| *pass == name && !done);
+
+        while ordered.len() < passes.len() {
+            let ready = (0..passes.len()).find(|&i| !placed[i] && passes[i].1.iter().all(|dependency| !is_pending(dependency, &placed)));
+            let Some(next) = ready.or_else(|| placed.iter().position(|done| !done)) else {
+                break;
+            };
+            placed[next] = true;
+            ordered.push(next);
+        }
+        ordered
+    }
+}
+
 /// Stub for parallelization configuration
 pub struct ParallelizationHints;
 
@@ -41,3 +64,20 @@ pub struct PassScheduler {
     /// Hints for parallel execution (stub)
     pub parallelization_hints: ParallelizationHints,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dependencies_run_first() {
+        let passes: [(&str, &[&str]); 3] = [("dce", &["inline"]), ("peephole", &[]), ("inline", &["missing"])];
+        assert_eq!(DependencyResolver.order(&passes), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn test_cycles_keep_original_order() {
+        let passes: [(&str, &[&str]); 2] = [("a", &["b"]), ("b", &["a"])];
+        assert_eq!(DependencyResolver.order(&passes), vec![0, 1]);
+    }
+}
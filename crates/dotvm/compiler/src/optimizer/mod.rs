@@ -22,33 +22,100 @@ pub mod passes;
 pub mod profiling;
 
 use crate::optimizer::framework::metrics::OptimizationMetrics as PipelineMetrics;
+use crate::optimizer::framework::pass::OptimizationPass;
 use crate::optimizer::framework::pipeline::{OptimizationConfig, OptimizationPipeline};
-use crate::optimizer::framework::scheduler::ExecutionStrategy;
+use crate::optimizer::framework::scheduler::{DependencyResolver, ExecutionStrategy};
 use crate::optimizer::passes as opt_passes;
-use crate::transpiler::types::TranspiledFunction;
+use crate::optimizer::passes::inlining::InlineConfig;
+use crate::optimizer::passes::peephole::{PeepholeConfig, PeepholeOptimizer, PeepholeStats};
+use crate::transpiler::types::{TranspiledFunction, TranspiledModule};
 use dotvm_core::bytecode::VmArchitecture;
 
 /// Main optimizer that coordinates all optimization passes
 pub struct Optimizer {
     pipeline: OptimizationPipeline,
+    peephole: PeepholeOptimizer,
 }
 
 impl Optimizer {
     /// Create a new optimizer with the given target architecture and level
     pub fn new(target_arch: VmArchitecture, optimization_level: u8) -> Self {
-        let config = OptimizationConfig { target_arch, optimization_level };
+        let config = OptimizationConfig {
+            target_arch,
+            optimization_level,
+            keep: Vec::new(),
+            peephole: PeepholeConfig::default(),
+            inline: InlineConfig::default(),
+        };
         let mut pipeline = OptimizationPipeline::new(config, ExecutionStrategy::Sequential);
         // Register optimization passes in pipeline order
         // TODO: Re-enable passes once they implement the new trait
         // pipeline.add_pass(passes::constant_folding::ConstantFolder::new());
         // pipeline.add_pass(passes::dead_code::DeadCodeEliminator::new());
-        // pipeline.add_pass(passes::peephole::PeepholeOptimizer::new(target_arch));
-        Self { pipeline }
+        Self {
+            pipeline,
+            peephole: PeepholeOptimizer::new(target_arch),
+        }
     }
 
-    /// Optimize a list of transpiled functions through the pipeline
+    /// Keep the named functions through dead code elimination, e.g. when they are invoked reflectively
+    pub fn keep_functions<I: IntoIterator<Item = String>>(&mut self, names: I) {
+        self.pipeline.config_mut().keep.extend(names);
+    }
+
+    /// Optimize a whole module: inline small functions, drop unreachable ones, then optimize the remaining ones
+    pub fn optimize_module(&mut self, mut module: TranspiledModule) -> TranspiledModule {
+        let mut inliner = opt_passes::FunctionInliner::new();
+        let mut dce = opt_passes::DeadCodeElimination::new();
+        let order = DependencyResolver.order(&[(inliner.name(), inliner.dependencies()), (dce.name(), dce.dependencies())]);
+        for pass in order {
+            module = match pass {
+                0 => self.run_module_pass(&mut inliner, module),
+                _ => self.run_module_pass(&mut dce, module),
+            };
+        }
+
+        let functions = std::mem::take(&mut module.functions);
+        module.functions = self.optimize(functions);
+        module
+    }
+
+    /// Set the inlining size limits
+    pub fn set_inline_config(&mut self, config: InlineConfig) {
+        self.pipeline.config_mut().inline = config;
+    }
+
+    fn run_module_pass<P>(&mut self, pass: &mut P, module: TranspiledModule) -> TranspiledModule
+    where
+        P: OptimizationPass<Input = TranspiledModule, Output = TranspiledModule, Config = OptimizationConfig>,
+    {
+        if !pass.can_optimize(&module, self.pipeline.config()) {
+            return module;
+        }
+        let result = pass.optimize(module, self.pipeline.config());
+        self.pipeline.record_result(pass.name(), &result);
+        result.output
+    }
+
+    /// Choose which peephole rules run
+    pub fn set_peephole_config(&mut self, config: PeepholeConfig) {
+        self.pipeline.config_mut().peephole = config;
+    }
+
+    /// Optimize a list of transpiled functions through the pipeline, then the peephole pass
     pub fn optimize(&mut self, functions: Vec<TranspiledFunction>) -> Vec<TranspiledFunction> {
-        self.pipeline.run(functions)
+        let functions = self.pipeline.run(functions);
+        functions
+            .into_iter()
+            .map(|function| {
+                if !self.peephole.can_optimize(&function, self.pipeline.config()) {
+                    return function;
+                }
+                let result = self.peephole.optimize(function, self.pipeline.config());
+                self.pipeline.record_result(self.peephole.name(), &result);
+                result.output
+            })
+            .collect()
     }
 
     /// Get pipeline-level optimization metrics
@@ -56,8 +123,14 @@ impl Optimizer {
         self.pipeline.metrics().clone()
     }
 
+    /// Per-rule peephole statistics
+    pub fn peephole_stats(&self) -> &PeepholeStats {
+        self.peephole.metrics()
+    }
+
     /// Reset all pipeline statistics and cache
     pub fn reset_stats(&mut self) {
         self.pipeline.reset();
+        self.peephole.reset_stats();
     }
 }
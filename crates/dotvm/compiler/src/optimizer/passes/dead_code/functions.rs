@@ -0,0 +1,350 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Module-level dead function elimination
+//!
+//! Builds a call graph over a [`TranspiledModule`], marks every function reachable
+//! from the module's roots and drops the rest, renumbering the surviving functions.
+//!
+//! Roots are exported functions, the start function, functions named in the
+//! config's `keep` list, and every function placed in a table by an element
+//! segment. The latter is conservative: any of them may be the target of an
+//! indirect call, which cannot be resolved statically.
+
+use crate::optimizer::framework::metrics::{OptimizationMetrics, OptimizationWarning};
+use crate::optimizer::framework::pass::{OptimizationPass, OptimizationResult};
+use crate::optimizer::framework::pipeline::OptimizationConfig;
+use crate::optimizer::passes::{CALL_OPCODES, REF_FUNC_OPCODES};
+use crate::transpiler::types::{ExportKind, Operand, TranspiledFunction, TranspiledInstruction, TranspiledModule};
+use std::collections::{HashMap, HashSet};
+
+/// Dead function elimination over a whole module
+pub struct DeadCodeElimination {
+    stats: DeadFunctionStats,
+}
+
+impl DeadCodeElimination {
+    /// Create a new dead function elimination pass
+    pub fn new() -> Self {
+        Self {
+            stats: DeadFunctionStats::default(),
+        }
+    }
+
+    /// Collect the functions referenced by `function`, as indices into the module's function index space
+    fn referenced_functions(function: &TranspiledFunction, names: &HashMap<&str, u32>) -> Vec<u32> {
+        let mut referenced = function.metadata.function_calls.clone();
+        for instruction in &function.instructions {
+            if !is_function_reference(instruction) {
+                continue;
+            }
+            match instruction.operands.first() {
+                Some(Operand::Immediate(index)) => referenced.push(*index),
+                Some(Operand::Label(name)) => referenced.extend(names.get(name.as_str()).copied()),
+                _ => {}
+            }
+        }
+        referenced
+    }
+
+    /// Compute the set of defined functions (by position in `module.functions`) reachable from the roots
+    fn reachable(&self, module: &TranspiledModule, keep: &[String], warnings: &mut Vec<OptimizationWarning>) -> HashSet<usize> {
+        let base = module.imported_function_count();
+        let names: HashMap<&str, u32> = module.functions.iter().enumerate().map(|(i, f)| (f.name.as_str(), base + i as u32)).collect();
+        let to_position = |index: u32| index.checked_sub(base).map(|i| i as usize).filter(|&i| i < module.functions.len());
+
+        let mut worklist: Vec<u32> = Vec::new();
+        worklist.extend(module.exports.iter().filter(|e| e.kind == ExportKind::Function).map(|e| e.index));
+        worklist.extend(module.start_function);
+        worklist.extend(module.element_segments.iter().flat_map(|segment| segment.function_indices.iter().copied()));
+        worklist.extend(module.functions.iter().enumerate().filter(|(_, f)| f.is_exported || keep.contains(&f.name)).map(|(i, _)| base + i as u32));
+
+        let mut live = HashSet::new();
+        while let Some(index) = worklist.pop() {
+            if index < base {
+                // Imported functions live outside the module
+                continue;
+            }
+            let Some(position) = to_position(index) else {
+                warnings.push(OptimizationWarning {
+                    pass_name: self.name().to_string(),
+                    message: format!("Reference to unknown function index {}", index),
+                });
+                continue;
+            };
+            if live.insert(position) {
+                worklist.extend(Self::referenced_functions(&module.functions[position], &names));
+            }
+        }
+        live
+    }
+}
+
+impl Default for DeadCodeElimination {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OptimizationPass for DeadCodeElimination {
+    type Input = TranspiledModule;
+    type Output = TranspiledModule;
+    type Config = OptimizationConfig;
+    type Metrics = DeadFunctionStats;
+
+    fn name(&self) -> &str {
+        "dead-function-elimination"
+    }
+
+    fn description(&self) -> &str {
+        "Removes functions unreachable from exports, the start function, tables and the keep list"
+    }
+
+    fn dependencies(&self) -> &[&str] {
+        // Inlining leaves callees without callers for this pass to remove
+        &["function-inlining"]
+    }
+
+    fn conflicts_with(&self) -> &[&str] {
+        &[]
+    }
+
+    fn can_optimize(&self, input: &Self::Input, config: &Self::Config) -> bool {
+        config.optimization_level > 0 && !input.functions.is_empty()
+    }
+
+    fn optimize(&mut self, mut input: Self::Input, config: &Self::Config) -> OptimizationResult<Self::Output> {
+        let mut warnings = Vec::new();
+        let live = self.reachable(&input, &config.keep, &mut warnings);
+
+        if live.len() == input.functions.len() {
+            return OptimizationResult {
+                output: input,
+                changed: false,
+                metrics: OptimizationMetrics::default(),
+                warnings,
+            };
+        }
+
+        // Old index -> new index for every surviving defined function
+        let base = input.imported_function_count();
+        let functions_removed = input.functions.len() - live.len();
+        let mut remap = HashMap::new();
+        let mut bytes_removed = 0;
+        let mut functions = Vec::with_capacity(live.len());
+        for (position, function) in std::mem::take(&mut input.functions).into_iter().enumerate() {
+            if live.contains(&position) {
+                remap.insert(base + position as u32, base + functions.len() as u32);
+                functions.push(function);
+            } else {
+                bytes_removed += estimated_code_size(&function);
+            }
+        }
+
+        let fix = |index: &mut u32| {
+            if let Some(&new_index) = remap.get(index) {
+                *index = new_index;
+            }
+        };
+        for function in &mut functions {
+            function.metadata.function_calls.iter_mut().for_each(fix);
+            for instruction in function.instructions.iter_mut().filter(|i| is_function_reference(i)) {
+                if let Some(Operand::Immediate(index)) = instruction.operands.first_mut() {
+                    fix(index);
+                }
+            }
+        }
+        input.exports.iter_mut().filter(|e| e.kind == ExportKind::Function).for_each(|e| fix(&mut e.index));
+        input.element_segments.iter_mut().flat_map(|s| s.function_indices.iter_mut()).for_each(fix);
+        if let Some(start) = input.start_function.as_mut() {
+            fix(start);
+        }
+        input.functions = functions;
+        input.metadata.estimated_size = input.metadata.estimated_size.saturating_sub(bytes_removed as u64);
+
+        self.stats.functions_removed += functions_removed;
+        self.stats.bytes_removed += bytes_removed;
+        self.stats.functions_retained = input.functions.len();
+
+        OptimizationResult {
+            output: input,
+            changed: true,
+            metrics: OptimizationMetrics {
+                functions_removed,
+                bytes_removed,
+                ..OptimizationMetrics::default()
+            },
+            warnings,
+        }
+    }
+
+    fn metrics(&self) -> &Self::Metrics {
+        &self.stats
+    }
+}
+
+/// Whether the instruction's first operand refers to a function
+fn is_function_reference(instruction: &TranspiledInstruction) -> bool {
+    let opcode = instruction.opcode.as_str();
+    CALL_OPCODES.contains(&opcode) || REF_FUNC_OPCODES.contains(&opcode)
+}
+
+/// Size of the function in the code section, mirroring the code generator's encoding
+pub(crate) fn estimated_code_size(function: &TranspiledFunction) -> usize {
+    // Function enter and exit markers, plus the local allocation when present
+    let framing = 2 + if function.local_count > 0 { 5 } else { 0 };
+    framing + function.instructions.iter().map(instruction_size).sum::<usize>()
+}
+
+/// Encoded size of a single instruction: the opcode plus its operands
+pub(crate) fn instruction_size(instruction: &TranspiledInstruction) -> usize {
+    let operands: usize = instruction
+        .operands
+        .iter()
+        .map(|operand| match operand {
+            Operand::Immediate(_) | Operand::Label(_) | Operand::Stack { .. } | Operand::Global { .. } => 4,
+            Operand::LargeImmediate(_) => 8,
+            Operand::Register(_) => 2,
+            Operand::Memory { .. } => 6,
+        })
+        .sum();
+    2 + operands
+}
+
+/// Statistics for dead function elimination
+#[derive(Debug, Clone, Default)]
+pub struct DeadFunctionStats {
+    /// Functions removed across all runs
+    pub functions_removed: usize,
+    /// Estimated bytes removed across all runs
+    pub bytes_removed: usize,
+    /// Functions left after the last run
+    pub functions_retained: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transpiler::types::{ElementSegment, ExportInfo, ImportInfo};
+    use dotvm_core::bytecode::{BytecodeHeader, VmArchitecture};
+
+    fn config(keep: &[&str]) -> OptimizationConfig {
+        OptimizationConfig {
+            target_arch: VmArchitecture::Arch64,
+            optimization_level: 2,
+            keep: keep.iter().map(|s| s.to_string()).collect(),
+            peephole: Default::default(),
+            inline: Default::default(),
+        }
+    }
+
+    fn function(name: &str, calls: &[u32]) -> TranspiledFunction {
+        let mut function = TranspiledFunction::new(name.to_string(), 0, 0);
+        for &index in calls {
+            function.add_instruction(TranspiledInstruction::new("CALL".to_string(), vec![Operand::immediate(index)]));
+        }
+        function.add_instruction(TranspiledInstruction::new("RETURN".to_string(), vec![]));
+        function
+    }
+
+    fn call_targets(function: &TranspiledFunction) -> Vec<u32> {
+        function
+            .instructions
+            .iter()
+            .filter_map(|i| match (i.opcode.as_str(), i.operands.first()) {
+                ("CALL", Some(Operand::Immediate(index))) => Some(*index),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn names(module: &TranspiledModule) -> Vec<&str> {
+        module.functions.iter().map(|f| f.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_removes_unreachable_functions_and_renumbers_calls() {
+        // Index 0 is imported; defined functions start at 1
+        let mut module = TranspiledModule::new(BytecodeHeader::new(VmArchitecture::Arch64));
+        module.add_import(ImportInfo::function("log".to_string(), "env".to_string(), 0));
+        module.add_function(function("main", &[3, 0]));
+        module.add_function(function("unused", &[2]));
+        module.add_function(function("helper", &[]));
+        module.add_export(ExportInfo::function("main".to_string(), 1));
+
+        let mut pass = DeadCodeElimination::new();
+        let result = pass.optimize(module, &config(&[]));
+
+        assert!(result.changed);
+        assert_eq!(names(&result.output), vec!["main", "helper"]);
+        assert_eq!(call_targets(&result.output.functions[0]), vec![2, 0]);
+        assert_eq!(result.output.exports[0].index, 1);
+        assert_eq!(result.metrics.functions_removed, 1);
+        assert_eq!(result.metrics.bytes_removed, 2 + (2 + 4) + 2);
+        assert_eq!(pass.metrics().functions_retained, 2);
+    }
+
+    #[test]
+    fn test_element_segment_targets_are_retained() {
+        let mut module = TranspiledModule::new(BytecodeHeader::new(VmArchitecture::Arch64));
+        module.add_function(function("main", &[]));
+        module.add_function(function("dead", &[]));
+        module.add_function(function("indirect_target", &[3]));
+        module.add_function(function("called_by_target", &[]));
+        module.add_export(ExportInfo::function("main".to_string(), 0));
+        module.add_element_segment(ElementSegment::new(0, Some(0), vec![2]));
+
+        let mut main = TranspiledInstruction::new("CALL_INDIRECT".to_string(), vec![Operand::immediate(0)]);
+        main.metadata.affects_control_flow = true;
+        module.functions[0].instructions.insert(0, main);
+
+        let result = DeadCodeElimination::new().optimize(module, &config(&[]));
+
+        assert_eq!(names(&result.output), vec!["main", "indirect_target", "called_by_target"]);
+        assert_eq!(result.output.element_segments[0].function_indices, vec![1]);
+        assert_eq!(call_targets(&result.output.functions[1]), vec![2]);
+        // The table index operand of the indirect call is not a function index
+        assert_eq!(result.output.functions[0].instructions[0].operands, vec![Operand::immediate(0)]);
+    }
+
+    #[test]
+    fn test_keep_list_and_start_function_are_roots() {
+        let mut module = TranspiledModule::new(BytecodeHeader::new(VmArchitecture::Arch64));
+        module.add_function(function("dead", &[]));
+        module.add_function(function("init", &[]));
+        module.add_function(function("reflective", &[]));
+        module.start_function = Some(1);
+
+        let result = DeadCodeElimination::new().optimize(module, &config(&["reflective"]));
+
+        assert_eq!(names(&result.output), vec!["init", "reflective"]);
+        assert_eq!(result.output.start_function, Some(0));
+    }
+
+    #[test]
+    fn test_unchanged_when_everything_is_reachable() {
+        let mut module = TranspiledModule::new(BytecodeHeader::new(VmArchitecture::Arch64));
+        module.add_function(function("main", &[1]));
+        module.add_function(function("helper", &[]));
+        module.add_export(ExportInfo::function("main".to_string(), 0));
+
+        let result = DeadCodeElimination::new().optimize(module, &config(&[]));
+
+        assert!(!result.changed);
+        assert_eq!(result.metrics.functions_removed, 0);
+        assert_eq!(result.output.functions.len(), 2);
+    }
+}
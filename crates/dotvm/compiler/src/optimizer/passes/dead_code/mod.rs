@@ -17,5 +17,7 @@
 //! Dead code elimination optimization pass
 
 pub mod eliminator;
+pub mod functions;
 
 pub use eliminator::DeadCodeEliminator;
+pub use functions::{DeadCodeElimination, DeadFunctionStats};
@@ -0,0 +1,132 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Call graph over the functions defined in a module
+
+use crate::optimizer::passes::{CALL_OPCODES, REF_FUNC_OPCODES};
+use crate::transpiler::types::{Operand, TranspiledInstruction, TranspiledModule};
+use std::collections::HashSet;
+
+/// Direct calls between the functions defined in a module, by position in `module.functions`
+pub(super) struct CallGraph {
+    callees: Vec<Vec<usize>>,
+    /// Functions that can reach themselves through direct calls, or are marked recursive
+    recursive: HashSet<usize>,
+    /// Functions placed in a table or referenced as values, which indirect calls may reach
+    address_taken: HashSet<usize>,
+}
+
+impl CallGraph {
+    pub(super) fn build(module: &TranspiledModule) -> Self {
+        let base = module.imported_function_count();
+        let to_position = |index: u32| index.checked_sub(base).map(|i| i as usize).filter(|&i| i < module.functions.len());
+
+        let callees = module
+            .functions
+            .iter()
+            .map(|function| {
+                let calls = function.instructions.iter().filter_map(call_target);
+                let mut callees: Vec<usize> = function.metadata.function_calls.iter().copied().chain(calls).filter_map(to_position).collect();
+                callees.sort_unstable();
+                callees.dedup();
+                callees
+            })
+            .collect();
+
+        let mut address_taken: HashSet<usize> = module
+            .element_segments
+            .iter()
+            .flat_map(|segment| segment.function_indices.iter().copied())
+            .filter_map(to_position)
+            .collect();
+        for instruction in module.functions.iter().flat_map(|function| function.instructions.iter()) {
+            if REF_FUNC_OPCODES.contains(&instruction.opcode.as_str())
+                && let Some(Operand::Immediate(index)) = instruction.operands.first()
+            {
+                address_taken.extend(to_position(*index));
+            }
+        }
+
+        let mut graph = Self {
+            callees,
+            recursive: HashSet::new(),
+            address_taken,
+        };
+        graph.recursive = (0..module.functions.len()).filter(|&f| module.functions[f].metadata.is_recursive || graph.reaches(f, f)).collect();
+        graph
+    }
+
+    pub(super) fn is_recursive(&self, position: usize) -> bool {
+        self.recursive.contains(&position)
+    }
+
+    pub(super) fn is_address_taken(&self, position: usize) -> bool {
+        self.address_taken.contains(&position)
+    }
+
+    /// Every function, each after the functions it calls unless they are part of a cycle
+    pub(super) fn bottom_up(&self) -> Vec<usize> {
+        let mut order = Vec::with_capacity(self.callees.len());
+        let mut visited = vec![false; self.callees.len()];
+        for root in 0..self.callees.len() {
+            if visited[root] {
+                continue;
+            }
+            visited[root] = true;
+            let mut stack = vec![(root, 0)];
+            while let Some(top) = stack.last_mut() {
+                let (function, next) = *top;
+                match self.callees[function].get(next) {
+                    Some(&callee) => {
+                        top.1 += 1;
+                        if !visited[callee] {
+                            visited[callee] = true;
+                            stack.push((callee, 0));
+                        }
+                    }
+                    None => {
+                        order.push(function);
+                        stack.pop();
+                    }
+                }
+            }
+        }
+        order
+    }
+
+    /// Whether `to` can be reached from `from` through at least one call
+    fn reaches(&self, from: usize, to: usize) -> bool {
+        let mut seen = HashSet::new();
+        let mut worklist = self.callees[from].clone();
+        while let Some(function) = worklist.pop() {
+            if function == to {
+                return true;
+            }
+            if seen.insert(function) {
+                worklist.extend(&self.callees[function]);
+            }
+        }
+        false
+    }
+}
+
+/// Index of the function called by a direct call
+pub(super) fn call_target(instruction: &TranspiledInstruction) -> Option<u32> {
+    match instruction.operands.first() {
+        Some(Operand::Immediate(index)) if CALL_OPCODES.contains(&instruction.opcode.as_str()) => Some(*index),
+        _ => None,
+    }
+}
@@ -0,0 +1,430 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Function inlining
+//!
+//! Replaces direct calls to small functions with the body of the callee. Callers
+//! are visited bottom-up over the call graph, so a callee has already had its own
+//! small calls inlined by the time it is considered.
+//!
+//! Only straight-line callees are inlined: no labels, no branches and no early
+//! return, just an optional trailing `return` or `end`. A callee must not declare
+//! locals beyond its parameters, must not be recursive, and must not have its
+//! address taken by an element segment or a function reference, since an indirect
+//! call could still reach it. Arguments are stored into locals appended to the
+//! caller and the callee's local accesses are renumbered onto them. A callee that
+//! starts by reading its parameters in order and never touches them again takes its
+//! arguments straight from the stack instead.
+//!
+//! The pass never removes a callee. Dead function elimination depends on it and
+//! drops the callees left without callers.
+
+mod call_graph;
+
+use crate::optimizer::framework::metrics::OptimizationMetrics;
+use crate::optimizer::framework::pass::{OptimizationPass, OptimizationResult};
+use crate::optimizer::framework::pipeline::OptimizationConfig;
+use crate::optimizer::passes::dead_code::functions::{estimated_code_size, instruction_size};
+use crate::transpiler::types::{Operand, TranspiledFunction, TranspiledInstruction, TranspiledModule};
+use call_graph::{CallGraph, call_target};
+
+/// Default size limit on inlined callees, in instructions
+pub const DEFAULT_MAX_CALLEE_INSTRUCTIONS: usize = 12;
+
+/// Default growth allowed per module, as a fraction of its code size
+pub const DEFAULT_MAX_MODULE_GROWTH: f64 = 0.2;
+
+/// Opcodes that may end a function body
+const RETURN_OPCODES: &[&str] = &["RETURN", "return", "end"];
+
+/// Opcodes that transfer control other than by a direct call
+const CONTROL_OPCODES: &[&str] = &[
+    "block",
+    "loop",
+    "if",
+    "else",
+    "end",
+    "br",
+    "br_if",
+    "br_table",
+    "return",
+    "unreachable",
+    "call_indirect",
+    "jump",
+    "JMP",
+    "JMPZ",
+    "JMPNZ",
+    "RETURN",
+    "CALL_INDIRECT",
+];
+
+/// Opcodes whose first operand is a local index
+const LOCAL_OPCODES: &[&str] = &["local.get", "local.set", "local.tee"];
+
+/// Inlining limits, carried in [`OptimizationConfig`]
+#[derive(Debug, Clone)]
+pub struct InlineConfig {
+    /// Largest callee inlined, in instructions not counting a trailing return
+    pub max_callee_instructions: usize,
+    /// Net code size a run may add to a module, as a fraction of the module's size before it
+    pub max_module_growth: f64,
+}
+
+impl Default for InlineConfig {
+    fn default() -> Self {
+        Self {
+            max_callee_instructions: DEFAULT_MAX_CALLEE_INSTRUCTIONS,
+            max_module_growth: DEFAULT_MAX_MODULE_GROWTH,
+        }
+    }
+}
+
+/// A callee body prepared for its call sites
+struct InlineBody {
+    /// Instructions without the trailing return, and without the parameter reads when `args_on_stack`
+    instructions: Vec<TranspiledInstruction>,
+    param_count: usize,
+    /// Whether the arguments can be left on the stack for the body to consume
+    args_on_stack: bool,
+    function_calls: Vec<u32>,
+}
+
+impl InlineBody {
+    /// The body of `callee`, if it can be inlined under `config`
+    fn prepare(callee: &TranspiledFunction, config: &InlineConfig) -> Option<Self> {
+        let mut instructions = callee.instructions.clone();
+        if instructions.last().is_some_and(|last| last.label.is_none() && RETURN_OPCODES.contains(&last.opcode.as_str())) {
+            instructions.pop();
+        }
+        if instructions.len() > config.max_callee_instructions || callee.local_count > callee.param_count {
+            return None;
+        }
+        let straight_line = instructions.iter().all(|instruction| {
+            instruction.label.is_none()
+                && !instruction.metadata.affects_control_flow
+                && !CONTROL_OPCODES.contains(&instruction.opcode.as_str())
+                && !instruction.operands.iter().any(|operand| matches!(operand, Operand::Label(_) | Operand::Stack { .. }))
+        });
+        if !straight_line {
+            return None;
+        }
+
+        let param_count = callee.param_count;
+        let reads_params_first = instructions.len() >= param_count
+            && instructions[..param_count]
+                .iter()
+                .enumerate()
+                .all(|(param, instruction)| instruction.opcode == "local.get" && local_index(instruction) == Some(param as u32));
+        let params_used_later = instructions[param_count.min(instructions.len())..]
+            .iter()
+            .any(|instruction| local_index(instruction).is_some_and(|index| (index as usize) < param_count));
+        let args_on_stack = reads_params_first && !params_used_later;
+        if args_on_stack {
+            instructions.drain(..param_count);
+        }
+
+        Some(Self {
+            instructions,
+            param_count,
+            args_on_stack,
+            function_calls: callee.metadata.function_calls.clone(),
+        })
+    }
+
+    /// The instructions replacing a call, with the callee's locals starting at `first_local` of the caller
+    fn expand(&self, first_local: u32) -> Vec<TranspiledInstruction> {
+        let mut expanded = Vec::with_capacity(self.instructions.len() + self.param_count);
+        if !self.args_on_stack {
+            // The last argument is on top of the stack
+            expanded.extend(
+                (0..self.param_count as u32)
+                    .rev()
+                    .map(|param| TranspiledInstruction::new("local.set".to_string(), vec![Operand::immediate(first_local + param)])),
+            );
+        }
+        expanded.extend(self.instructions.iter().cloned().map(|mut instruction| {
+            if LOCAL_OPCODES.contains(&instruction.opcode.as_str())
+                && let Some(Operand::Immediate(index)) = instruction.operands.first_mut()
+            {
+                *index += first_local;
+            }
+            instruction
+        }));
+        expanded
+    }
+}
+
+/// Local index accessed by a local instruction
+fn local_index(instruction: &TranspiledInstruction) -> Option<u32> {
+    match instruction.operands.first() {
+        Some(Operand::Immediate(index)) if LOCAL_OPCODES.contains(&instruction.opcode.as_str()) => Some(*index),
+        _ => None,
+    }
+}
+
+/// Function inlining over a whole module
+pub struct FunctionInliner {
+    stats: InlineStats,
+}
+
+impl FunctionInliner {
+    /// Create a new function inlining pass
+    pub fn new() -> Self {
+        Self { stats: InlineStats::default() }
+    }
+}
+
+impl Default for FunctionInliner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OptimizationPass for FunctionInliner {
+    type Input = TranspiledModule;
+    type Output = TranspiledModule;
+    type Config = OptimizationConfig;
+    type Metrics = InlineStats;
+
+    fn name(&self) -> &str {
+        "function-inlining"
+    }
+
+    fn description(&self) -> &str {
+        "Replaces calls to small straight-line functions with their bodies, within a module growth budget"
+    }
+
+    fn dependencies(&self) -> &[&str] {
+        &[]
+    }
+
+    fn conflicts_with(&self) -> &[&str] {
+        &[]
+    }
+
+    fn can_optimize(&self, input: &Self::Input, config: &Self::Config) -> bool {
+        config.optimization_level >= 2 && config.inline.max_callee_instructions > 0 && input.functions.len() > 1
+    }
+
+    fn optimize(&mut self, mut input: Self::Input, config: &Self::Config) -> OptimizationResult<Self::Output> {
+        let graph = CallGraph::build(&input);
+        let base = input.imported_function_count();
+        let module_size: usize = input.functions.iter().map(estimated_code_size).sum();
+        let budget = (module_size as f64 * config.inline.max_module_growth.max(0.0)) as isize;
+        let mut growth = 0isize;
+        let mut metrics = OptimizationMetrics::default();
+
+        for caller in graph.bottom_up() {
+            // Inlined bodies never overlap, so every call site can reuse the same extra locals
+            let first_local = input.functions[caller].local_count;
+            let mut local_count = first_local;
+            let mut function_calls = input.functions[caller].metadata.function_calls.clone();
+            let mut instructions = Vec::with_capacity(input.functions[caller].instructions.len());
+
+            for instruction in std::mem::take(&mut input.functions[caller].instructions) {
+                let callee = call_target(&instruction)
+                    .and_then(|index| index.checked_sub(base))
+                    .map(|position| position as usize)
+                    .filter(|&position| position < input.functions.len() && position != caller && !graph.is_recursive(position) && !graph.is_address_taken(position));
+                let Some((callee_index, body)) = callee.and_then(|position| Some((base + position as u32, InlineBody::prepare(&input.functions[position], &config.inline)?))) else {
+                    instructions.push(instruction);
+                    continue;
+                };
+
+                let mut expanded = body.expand(first_local as u32);
+                if let Some(label) = &instruction.label {
+                    // Jumps to the call now land on the inlined body
+                    if expanded.is_empty() {
+                        expanded.push(TranspiledInstruction::new("nop".to_string(), vec![]));
+                    }
+                    expanded[0].label = Some(label.clone());
+                }
+                let added: usize = expanded.iter().map(instruction_size).sum();
+                let site_growth = added as isize - instruction_size(&instruction) as isize;
+                if site_growth > 0 && growth + site_growth > budget {
+                    self.stats.over_budget += 1;
+                    instructions.push(instruction);
+                    continue;
+                }
+
+                growth += site_growth;
+                metrics.call_sites_inlined += 1;
+                metrics.bytes_added += added;
+                if !body.args_on_stack {
+                    local_count = local_count.max(first_local + body.param_count);
+                }
+                if let Some(call) = function_calls.iter().position(|&index| index == callee_index) {
+                    function_calls.remove(call);
+                }
+                function_calls.extend(&body.function_calls);
+                instructions.extend(expanded);
+            }
+
+            let function = &mut input.functions[caller];
+            function.instructions = instructions;
+            function.local_count = local_count;
+            function.metadata.function_calls = function_calls;
+        }
+
+        input.metadata.estimated_size = input.metadata.estimated_size.saturating_add_signed(growth as i64);
+        self.stats.call_sites_inlined += metrics.call_sites_inlined;
+        self.stats.bytes_added += metrics.bytes_added;
+
+        OptimizationResult {
+            output: input,
+            changed: metrics.call_sites_inlined > 0,
+            metrics,
+            warnings: Vec::new(),
+        }
+    }
+
+    fn metrics(&self) -> &Self::Metrics {
+        &self.stats
+    }
+}
+
+/// Statistics for function inlining
+#[derive(Debug, Clone, Default)]
+pub struct InlineStats {
+    /// Call sites inlined across all runs
+    pub call_sites_inlined: usize,
+    /// Estimated bytes of inlined code across all runs
+    pub bytes_added: usize,
+    /// Call sites left alone because inlining them would exceed the growth budget
+    pub over_budget: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::passes::dead_code::DeadCodeElimination;
+    use crate::transpiler::types::{ElementSegment, ExportInfo};
+    use dotvm_core::bytecode::{BytecodeHeader, VmArchitecture};
+
+    fn config(max_module_growth: f64) -> OptimizationConfig {
+        OptimizationConfig {
+            target_arch: VmArchitecture::Arch64,
+            optimization_level: 2,
+            keep: Vec::new(),
+            peephole: Default::default(),
+            inline: InlineConfig {
+                max_module_growth,
+                ..InlineConfig::default()
+            },
+        }
+    }
+
+    fn instruction(opcode: &str, operands: &[u32]) -> TranspiledInstruction {
+        TranspiledInstruction::new(opcode.to_string(), operands.iter().map(|&operand| Operand::immediate(operand)).collect())
+    }
+
+    fn function(name: &str, param_count: usize, body: &[(&str, &[u32])]) -> TranspiledFunction {
+        let mut function = TranspiledFunction::new(name.to_string(), param_count, param_count);
+        for (opcode, operands) in body {
+            function.add_instruction(instruction(opcode, operands));
+            if *opcode == "call" {
+                function.metadata.add_function_call(operands[0]);
+            }
+        }
+        function.add_instruction(instruction("return", &[]));
+        function
+    }
+
+    fn instruction_count(module: &TranspiledModule) -> usize {
+        module.functions.iter().map(TranspiledFunction::instruction_count).sum()
+    }
+
+    fn opcodes(function: &TranspiledFunction) -> Vec<&str> {
+        function.instructions.iter().map(|instruction| instruction.opcode.as_str()).collect()
+    }
+
+    #[test]
+    fn test_inlining_getters_shrinks_the_module() {
+        // main(base) reads eight fields of a struct through one getter each
+        let mut module = TranspiledModule::new(BytecodeHeader::new(VmArchitecture::Arch64));
+        let mut main = TranspiledFunction::new("main".to_string(), 1, 1);
+        for field in 0..8u32 {
+            main.add_instruction(instruction("local.get", &[0]));
+            main.add_instruction(instruction("call", &[field + 1]));
+            main.add_instruction(instruction("drop", &[]));
+            main.metadata.add_function_call(field + 1);
+        }
+        main.add_instruction(instruction("return", &[]));
+        module.add_function(main);
+        for field in 0..8u32 {
+            module.add_function(function(&format!("get_field_{field}"), 1, &[("local.get", &[0]), ("i32.load", &[field * 4])]));
+        }
+        module.add_export(ExportInfo::function("main".to_string(), 0));
+        let before = instruction_count(&module);
+
+        let config = config(DEFAULT_MAX_MODULE_GROWTH);
+        let mut inliner = FunctionInliner::new();
+        let inlined = inliner.optimize(module, &config);
+        let result = DeadCodeElimination::new().optimize(inlined.output, &config);
+
+        assert!(inlined.changed);
+        assert_eq!(inlined.metrics.call_sites_inlined, 8);
+        assert_eq!(inlined.metrics.bytes_added, 8 * (2 + 4));
+        assert_eq!(result.metrics.functions_removed, 8);
+
+        let main = &result.output.functions[0];
+        assert!(main.metadata.function_calls.is_empty());
+        assert_eq!(main.local_count, 1);
+        assert_eq!(opcodes(main)[..3], ["local.get", "i32.load", "drop"]);
+        assert_eq!(main.instructions[1].operands, vec![Operand::immediate(0)]);
+        assert_eq!(before, 8 * 3 + 1 + 8 * 3);
+        assert_eq!(instruction_count(&result.output), 8 * 3 + 1);
+    }
+
+    #[test]
+    fn test_recursive_and_table_functions_are_not_inlined() {
+        let mut module = TranspiledModule::new(BytecodeHeader::new(VmArchitecture::Arch64));
+        module.add_function(function("main", 0, &[("call", &[1]), ("call", &[2]), ("call", &[3])]));
+        module.add_function(function("countdown", 0, &[("call", &[1])]));
+        module.add_function(function("handler", 0, &[("i32.const", &[1]), ("drop", &[])]));
+        module.add_function(function("ping", 0, &[("call", &[4])]));
+        module.add_function(function("pong", 0, &[("call", &[3])]));
+        module.add_export(ExportInfo::function("main".to_string(), 0));
+        module.add_element_segment(ElementSegment::new(0, Some(0), vec![2]));
+
+        let result = FunctionInliner::new().optimize(module, &config(1.0));
+
+        assert!(!result.changed);
+        assert_eq!(opcodes(&result.output.functions[0]), ["call", "call", "call", "return"]);
+    }
+
+    #[test]
+    fn test_growth_budget_limits_call_sites() {
+        // sub(a, b) reads its parameters out of order, so arguments go through locals
+        let mut module = TranspiledModule::new(BytecodeHeader::new(VmArchitecture::Arch64));
+        let site: [(&str, &[u32]); 4] = [("i32.const", &[9]), ("i32.const", &[4]), ("call", &[1]), ("drop", &[])];
+        module.add_function(function("main", 0, &site.repeat(3)));
+        module.add_function(function("sub", 2, &[("local.get", &[1]), ("local.get", &[0]), ("i32.sub", &[])]));
+        module.add_export(ExportInfo::function("main".to_string(), 0));
+
+        // Each site grows the module by 26 - 6 bytes, out of 64 + 23
+        let mut inliner = FunctionInliner::new();
+        let result = inliner.optimize(module, &config(0.3));
+
+        assert_eq!(result.metrics.call_sites_inlined, 1);
+        assert_eq!(inliner.metrics().over_budget, 2);
+        let main = &result.output.functions[0];
+        assert_eq!(main.local_count, 2);
+        assert_eq!(opcodes(main)[2..7], ["local.set", "local.set", "local.get", "local.get", "i32.sub"]);
+        assert_eq!(main.instructions[2].operands, vec![Operand::immediate(1)]);
+        assert_eq!(main.metadata.function_calls, vec![1, 1]);
+    }
+}
@@ -18,9 +18,18 @@
 
 pub mod constant_folding;
 pub mod dead_code;
+pub mod inlining;
 pub mod peephole;
 
+/// Opcodes calling the function whose index is their first operand, as the translator
+/// (wasm names) and hand-written DotVM code spell them
+pub(crate) const CALL_OPCODES: &[&str] = &["CALL", "call"];
+
+/// Opcodes taking a reference to the function whose index is their first operand
+pub(crate) const REF_FUNC_OPCODES: &[&str] = &["REF_FUNC", "ref.func"];
+
 // Re-export main types for convenience
 pub use constant_folding::ConstantFolder;
-pub use dead_code::DeadCodeEliminator;
-pub use peephole::PeepholeOptimizer;
+pub use dead_code::{DeadCodeElimination, DeadCodeEliminator};
+pub use inlining::{FunctionInliner, InlineConfig, InlineStats};
+pub use peephole::{PeepholeConfig, PeepholeOptimizer, PeepholeRule, PeepholeStats};
@@ -15,12 +15,114 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 //! Peephole optimization pass
+//!
+//! Rewrites short instruction windows using the rules in [`rules`], repeating
+//! over a function until no rule matches or the iteration cap is reached.
+
+mod rules;
 
 use crate::optimizer::framework::metrics::{OptimizationMetrics, OptimizationWarning};
 use crate::optimizer::framework::pass::{OptimizationPass, OptimizationResult};
 use crate::optimizer::framework::pipeline::OptimizationConfig;
 use crate::transpiler::types::{TranspiledFunction, TranspiledInstruction};
 use dotvm_core::bytecode::VmArchitecture;
+use std::collections::HashMap;
+
+/// Default cap on passes over a single function
+pub const DEFAULT_MAX_ITERATIONS: usize = 16;
+
+/// Individual peephole rewrite rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PeepholeRule {
+    /// Arithmetic on two immediate operands
+    ConstantFolding,
+    /// A pure push immediately popped
+    PushPop,
+    /// A duplicate immediately dropped
+    DupDrop,
+    /// Reloading a value that was just stored
+    LoadAfterStore,
+    /// An unconditional jump to the following instruction
+    JumpToNext,
+    /// Multiplication by a power of two as a shift
+    StrengthReduction,
+}
+
+impl PeepholeRule {
+    /// Rules in the order they are tried at each position
+    pub const ALL: [PeepholeRule; 6] = [
+        PeepholeRule::JumpToNext,
+        PeepholeRule::PushPop,
+        PeepholeRule::DupDrop,
+        PeepholeRule::LoadAfterStore,
+        PeepholeRule::ConstantFolding,
+        PeepholeRule::StrengthReduction,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PeepholeRule::ConstantFolding => "constant-folding",
+            PeepholeRule::PushPop => "push-pop",
+            PeepholeRule::DupDrop => "dup-drop",
+            PeepholeRule::LoadAfterStore => "load-after-store",
+            PeepholeRule::JumpToNext => "jump-to-next",
+            PeepholeRule::StrengthReduction => "strength-reduction",
+        }
+    }
+}
+
+/// Peephole settings, carried in [`OptimizationConfig`]
+#[derive(Debug, Clone)]
+pub struct PeepholeConfig {
+    pub constant_folding: bool,
+    pub push_pop: bool,
+    pub dup_drop: bool,
+    pub load_after_store: bool,
+    pub jump_to_next: bool,
+    pub strength_reduction: bool,
+    /// Passes over a function before giving up on reaching a fixed point
+    pub max_iterations: usize,
+}
+
+impl Default for PeepholeConfig {
+    fn default() -> Self {
+        Self {
+            constant_folding: true,
+            push_pop: true,
+            dup_drop: true,
+            load_after_store: true,
+            jump_to_next: true,
+            strength_reduction: true,
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+        }
+    }
+}
+
+impl PeepholeConfig {
+    pub fn is_enabled(&self, rule: PeepholeRule) -> bool {
+        match rule {
+            PeepholeRule::ConstantFolding => self.constant_folding,
+            PeepholeRule::PushPop => self.push_pop,
+            PeepholeRule::DupDrop => self.dup_drop,
+            PeepholeRule::LoadAfterStore => self.load_after_store,
+            PeepholeRule::JumpToNext => self.jump_to_next,
+            PeepholeRule::StrengthReduction => self.strength_reduction,
+        }
+    }
+
+    /// Enable or disable a single rule
+    pub fn set_enabled(&mut self, rule: PeepholeRule, enabled: bool) {
+        let flag = match rule {
+            PeepholeRule::ConstantFolding => &mut self.constant_folding,
+            PeepholeRule::PushPop => &mut self.push_pop,
+            PeepholeRule::DupDrop => &mut self.dup_drop,
+            PeepholeRule::LoadAfterStore => &mut self.load_after_store,
+            PeepholeRule::JumpToNext => &mut self.jump_to_next,
+            PeepholeRule::StrengthReduction => &mut self.strength_reduction,
+        };
+        *flag = enabled;
+    }
+}
 
 /// Peephole optimizer for DotVM bytecode
 pub struct PeepholeOptimizer {
@@ -36,8 +138,42 @@ impl PeepholeOptimizer {
             stats: PeepholeStats::default(),
         }
     }
-}
 
+    /// Reset peephole statistics
+    pub fn reset_stats(&mut self) {
+        self.stats = PeepholeStats::default();
+    }
+
+    /// One left-to-right pass, trying every enabled rule at each position
+    fn run_pass(&mut self, instructions: &[TranspiledInstruction], config: &PeepholeConfig) -> Option<Vec<TranspiledInstruction>> {
+        let mut output = Vec::with_capacity(instructions.len());
+        let mut changed = false;
+        let mut i = 0;
+
+        while i < instructions.len() {
+            let window = &instructions[i..];
+            let matched = PeepholeRule::ALL
+                .into_iter()
+                .filter(|&rule| config.is_enabled(rule))
+                .find_map(|rule| rules::apply(rule, window, self.target_arch).map(|rewrite| (rule, rewrite)));
+
+            match matched {
+                Some((rule, rewrite)) => {
+                    self.stats.record(rule, rewrite.consumed, rewrite.replacement.len());
+                    output.extend(rewrite.replacement);
+                    i += rewrite.consumed;
+                    changed = true;
+                }
+                None => {
+                    output.push(instructions[i].clone());
+                    i += 1;
+                }
+            }
+        }
+
+        changed.then_some(output)
+    }
+}
 impl OptimizationPass for PeepholeOptimizer {
     type Input = TranspiledFunction;
     type Output = TranspiledFunction;
@@ -49,7 +185,7 @@ impl OptimizationPass for PeepholeOptimizer {
     }
 
     fn description(&self) -> &str {
-        "Peephole optimization pass"
+        "Rewrites short instruction sequences into cheaper equivalents"
     }
 
     fn dependencies(&self) -> &[&str] {
@@ -60,17 +196,41 @@ impl OptimizationPass for PeepholeOptimizer {
         &[]
     }
 
-    fn can_optimize(&self, _input: &Self::Input, _config: &Self::Config) -> bool {
-        true
+    fn can_optimize(&self, input: &Self::Input, config: &Self::Config) -> bool {
+        config.optimization_level > 0 && !input.instructions.is_empty() && PeepholeRule::ALL.into_iter().any(|rule| config.peephole.is_enabled(rule))
     }
 
-    fn optimize(&mut self, input: Self::Input, _config: &Self::Config) -> OptimizationResult<Self::Output> {
-        // Simple pass-through for now
+    fn optimize(&mut self, mut input: Self::Input, config: &Self::Config) -> OptimizationResult<Self::Output> {
+        let mut changed = false;
+        let mut warnings = Vec::new();
+        let mut iterations = 0;
+
+        loop {
+            if iterations == config.peephole.max_iterations {
+                self.stats.iteration_limit_hits += 1;
+                warnings.push(OptimizationWarning {
+                    pass_name: self.name().to_string(),
+                    message: format!("Function '{}' did not reach a fixed point within {} iterations", input.name, iterations),
+                });
+                break;
+            }
+            iterations += 1;
+
+            match self.run_pass(&input.instructions, &config.peephole) {
+                Some(instructions) => {
+                    input.instructions = instructions;
+                    changed = true;
+                }
+                None => break,
+            }
+        }
+        self.stats.iterations += iterations;
+
         OptimizationResult {
             output: input,
-            changed: false,
+            changed,
             metrics: OptimizationMetrics::default(),
-            warnings: Vec::new(),
+            warnings,
         }
     }
 
@@ -85,4 +245,255 @@ pub struct PeepholeStats {
     pub patterns_matched: usize,
     pub instructions_eliminated: usize,
     pub instructions_combined: usize,
+    /// Matches per rule
+    pub rule_matches: HashMap<PeepholeRule, usize>,
+    /// Passes run over all functions, including the final one that matched nothing
+    pub iterations: usize,
+    /// Functions that hit the iteration cap before reaching a fixed point
+    pub iteration_limit_hits: usize,
+}
+
+impl PeepholeStats {
+    /// Number of times `rule` matched
+    pub fn matches(&self, rule: PeepholeRule) -> usize {
+        self.rule_matches.get(&rule).copied().unwrap_or(0)
+    }
+
+    fn record(&mut self, rule: PeepholeRule, consumed: usize, produced: usize) {
+        self.patterns_matched += 1;
+        *self.rule_matches.entry(rule).or_default() += 1;
+        self.instructions_eliminated += consumed.saturating_sub(produced);
+        if produced > 0 && produced < consumed {
+            self.instructions_combined += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transpiler::types::Operand;
+
+    fn inst(opcode: &str, operands: Vec<Operand>) -> TranspiledInstruction {
+        TranspiledInstruction::new(opcode.to_string(), operands)
+    }
+
+    fn imm(opcode: &str, value: u32) -> TranspiledInstruction {
+        inst(opcode, vec![Operand::immediate(value)])
+    }
+
+    fn config() -> OptimizationConfig {
+        OptimizationConfig {
+            target_arch: VmArchitecture::Arch64,
+            optimization_level: 2,
+            keep: Vec::new(),
+            peephole: PeepholeConfig::default(),
+            inline: Default::default(),
+        }
+    }
+
+    fn run(pass: &mut PeepholeOptimizer, instructions: Vec<TranspiledInstruction>, config: &OptimizationConfig) -> OptimizationResult<TranspiledFunction> {
+        let mut function = TranspiledFunction::new("f".to_string(), 0, 0);
+        instructions.into_iter().for_each(|i| function.add_instruction(i));
+        pass.optimize(function, config)
+    }
+
+    /// Opcodes and operands in a compact form for comparison
+    fn listing(function: &TranspiledFunction) -> Vec<String> {
+        function
+            .instructions
+            .iter()
+            .map(|i| {
+                let operands: Vec<String> = i
+                    .operands
+                    .iter()
+                    .map(|o| match o {
+                        Operand::Immediate(v) => v.to_string(),
+                        Operand::LargeImmediate(v) => v.to_string(),
+                        Operand::Label(l) => l.clone(),
+                        other => format!("{other:?}"),
+                    })
+                    .collect();
+                let label = i.label.as_ref().map(|l| format!("{l}: ")).unwrap_or_default();
+                format!("{label}{} {}", i.opcode, operands.join(" ")).trim_end().to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_constant_folding_reaches_fixed_point() {
+        let mut pass = PeepholeOptimizer::new(VmArchitecture::Arch64);
+        let result = run(
+            &mut pass,
+            vec![imm("i32.const", 2), imm("i32.const", 3), inst("i32.add", vec![]), imm("i32.const", 7), inst("i32.mul", vec![])],
+            &config(),
+        );
+
+        assert!(result.changed);
+        assert_eq!(listing(&result.output), vec!["i32.const 35"]);
+        assert_eq!(pass.metrics().matches(PeepholeRule::ConstantFolding), 2);
+        assert_eq!(pass.metrics().instructions_eliminated, 4);
+        assert_eq!(pass.metrics().iterations, 3);
+    }
+
+    #[test]
+    fn test_constant_folding_wraps_and_skips_division() {
+        let mut pass = PeepholeOptimizer::new(VmArchitecture::Arch64);
+        // -1 arrives sign-extended from the transpiler
+        let minus_one = inst("i32.const", vec![Operand::large_immediate(u64::MAX)]);
+        let result = run(
+            &mut pass,
+            vec![
+                minus_one,
+                imm("i32.const", 1),
+                inst("i32.add", vec![]),
+                imm("i64.const", 1),
+                imm("i64.const", 0),
+                inst("i64.div_u", vec![]),
+            ],
+            &config(),
+        );
+
+        assert_eq!(listing(&result.output), vec!["i32.const 0", "i64.const 1", "i64.const 0", "i64.div_u"]);
+    }
+
+    #[test]
+    fn test_push_pop_and_dup_drop_elimination() {
+        let mut pass = PeepholeOptimizer::new(VmArchitecture::Arch64);
+        let result = run(
+            &mut pass,
+            vec![
+                imm("local.get", 0),
+                inst("drop", vec![]),
+                inst("dup", vec![]),
+                inst("drop", vec![]),
+                imm("local.tee", 1),
+                inst("drop", vec![]),
+            ],
+            &config(),
+        );
+
+        assert_eq!(listing(&result.output), vec!["local.set 1"]);
+        assert_eq!(pass.metrics().matches(PeepholeRule::PushPop), 1);
+        assert_eq!(pass.metrics().matches(PeepholeRule::DupDrop), 2);
+    }
+
+    #[test]
+    fn test_load_after_store() {
+        let mut pass = PeepholeOptimizer::new(VmArchitecture::Arch64);
+        let result = run(
+            &mut pass,
+            vec![
+                imm("local.set", 2),
+                imm("local.get", 2),
+                imm("global.set", 0),
+                imm("global.get", 0),
+                imm("local.set", 3),
+                imm("local.get", 4),
+            ],
+            &config(),
+        );
+
+        assert_eq!(listing(&result.output), vec!["local.tee 2", "dup", "global.set 0", "local.set 3", "local.get 4"]);
+        assert_eq!(pass.metrics().matches(PeepholeRule::LoadAfterStore), 2);
+    }
+
+    #[test]
+    fn test_jump_to_next_instruction() {
+        let mut pass = PeepholeOptimizer::new(VmArchitecture::Arch64);
+        let jump = |target: &str| inst("br", vec![Operand::Label(target.to_string())]);
+        let result = run(
+            &mut pass,
+            vec![
+                jump("next"),
+                imm("local.get", 0).with_label("next".to_string()),
+                jump("far"),
+                imm("local.get", 1),
+                imm("local.get", 2).with_label("far".to_string()),
+            ],
+            &config(),
+        );
+
+        assert_eq!(listing(&result.output), vec!["next: local.get 0", "br far", "local.get 1", "far: local.get 2"]);
+    }
+
+    #[test]
+    fn test_strength_reduction_depends_on_architecture() {
+        let sequence = || vec![imm("local.get", 0), imm("i64.const", 8), inst("i64.mul", vec![]), imm("i32.const", 1), inst("i32.mul", vec![])];
+
+        let mut pass = PeepholeOptimizer::new(VmArchitecture::Arch64);
+        let result = run(&mut pass, sequence(), &config());
+        assert_eq!(listing(&result.output), vec!["local.get 0", "i64.const 3", "i64.shl"]);
+        assert_eq!(pass.metrics().matches(PeepholeRule::StrengthReduction), 2);
+
+        // 64-bit shifts are not native on a 32-bit target; multiplying by one is still dropped
+        let mut pass = PeepholeOptimizer::new(VmArchitecture::Arch32);
+        let result = run(&mut pass, sequence(), &config());
+        assert_eq!(listing(&result.output), vec!["local.get 0", "i64.const 8", "i64.mul"]);
+    }
+
+    #[test]
+    fn test_labels_inside_window_block_rewrites() {
+        let mut pass = PeepholeOptimizer::new(VmArchitecture::Arch64);
+        let result = run(
+            &mut pass,
+            vec![
+                imm("i32.const", 1).with_label("top".to_string()),
+                imm("i32.const", 2),
+                inst("i32.add", vec![]),
+                imm("i32.const", 3),
+                imm("i32.const", 4).with_label("mid".to_string()),
+                inst("i32.add", vec![]),
+                imm("local.get", 0).with_label("gone".to_string()),
+                inst("drop", vec![]),
+            ],
+            &config(),
+        );
+
+        assert_eq!(
+            listing(&result.output),
+            vec!["top: i32.const 3", "i32.const 3", "mid: i32.const 4", "i32.add", "gone: local.get 0", "drop"]
+        );
+    }
+
+    #[test]
+    fn test_rules_can_be_disabled() {
+        let mut config = config();
+        config.peephole.set_enabled(PeepholeRule::ConstantFolding, false);
+        config.peephole.strength_reduction = false;
+
+        let mut pass = PeepholeOptimizer::new(VmArchitecture::Arch64);
+        let result = run(&mut pass, vec![imm("i32.const", 2), imm("i32.const", 4), inst("i32.mul", vec![])], &config);
+
+        assert!(!result.changed);
+        assert_eq!(pass.metrics().patterns_matched, 0);
+
+        config.peephole = PeepholeConfig {
+            constant_folding: false,
+            push_pop: false,
+            dup_drop: false,
+            load_after_store: false,
+            jump_to_next: false,
+            strength_reduction: false,
+            ..Default::default()
+        };
+        assert!(!pass.can_optimize(&result.output, &config));
+    }
+
+    #[test]
+    fn test_iteration_cap() {
+        let mut config = config();
+        config.peephole.max_iterations = 1;
+
+        let mut pass = PeepholeOptimizer::new(VmArchitecture::Arch64);
+        let result = run(
+            &mut pass,
+            vec![imm("i32.const", 1), imm("i32.const", 2), inst("i32.add", vec![]), imm("i32.const", 3), inst("i32.add", vec![])],
+            &config,
+        );
+
+        assert_eq!(listing(&result.output), vec!["i32.const 3", "i32.const 3", "i32.add"]);
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(pass.metrics().iteration_limit_hits, 1);
+    }
 }
@@ -0,0 +1,240 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Peephole rewrite rules
+//!
+//! Each rule inspects a short window starting at one instruction and, when it
+//! matches, says how many instructions it consumed and what replaces them.
+//! Only the first instruction of a window may carry a label: anything jumping
+//! into the middle of a window would observe the rewritten sequence.
+
+use super::PeepholeRule;
+use crate::transpiler::types::{Operand, TranspiledInstruction};
+use dotvm_core::bytecode::VmArchitecture;
+
+/// Unconditional jumps taking a label operand
+const JUMP_OPCODES: &[&str] = &["br", "jump", "JMP"];
+
+/// Instructions that only push a value, so an immediate pop cancels them
+const PURE_PUSH_OPCODES: &[&str] = &["i32.const", "i64.const", "f32.const", "f64.const", "local.get", "global.get", "push"];
+
+/// Instructions that discard the top of the stack
+const POP_OPCODES: &[&str] = &["drop", "pop"];
+
+/// A matched window and what replaces it
+pub(super) struct Rewrite {
+    pub consumed: usize,
+    pub replacement: Vec<TranspiledInstruction>,
+}
+
+impl Rewrite {
+    fn new(consumed: usize, replacement: Vec<TranspiledInstruction>) -> Self {
+        Self { consumed, replacement }
+    }
+}
+
+/// Try `rule` on the window starting at `window[0]`
+pub(super) fn apply(rule: PeepholeRule, window: &[TranspiledInstruction], target_arch: VmArchitecture) -> Option<Rewrite> {
+    let mut rewrite = match rule {
+        PeepholeRule::ConstantFolding => fold_constants(window),
+        PeepholeRule::PushPop => push_pop(window),
+        PeepholeRule::DupDrop => dup_drop(window),
+        PeepholeRule::LoadAfterStore => load_after_store(window),
+        PeepholeRule::JumpToNext => jump_to_next(window),
+        PeepholeRule::StrengthReduction => strength_reduce(window, target_arch),
+    }?;
+
+    let matched = &window[..rewrite.consumed];
+    if matched[1..].iter().any(|i| i.label.is_some()) {
+        return None;
+    }
+    // The window's label and source location move to the first replacement; a labeled window cannot vanish entirely
+    if let Some(label) = &matched[0].label {
+        rewrite.replacement.first_mut()?.label = Some(label.clone());
+    }
+    for instruction in &mut rewrite.replacement {
+        instruction.source_location = matched[0].source_location.clone();
+    }
+
+    Some(rewrite)
+}
+
+/// Integer width of a typed opcode such as `i64.add`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Width {
+    I32,
+    I64,
+}
+
+impl Width {
+    /// Split a typed opcode into its width and operation
+    fn split(opcode: &str) -> Option<(Width, &str)> {
+        match opcode.split_once('.')? {
+            ("i32", op) => Some((Width::I32, op)),
+            ("i64", op) => Some((Width::I64, op)),
+            _ => None,
+        }
+    }
+
+    fn prefix(self) -> &'static str {
+        match self {
+            Width::I32 => "i32",
+            Width::I64 => "i64",
+        }
+    }
+
+    fn bits(self) -> u32 {
+        match self {
+            Width::I32 => 32,
+            Width::I64 => 64,
+        }
+    }
+
+    fn truncate(self, value: u64) -> u64 {
+        match self {
+            Width::I32 => value as u32 as u64,
+            Width::I64 => value,
+        }
+    }
+}
+
+/// Value of an integer constant instruction
+fn constant(instruction: &TranspiledInstruction) -> Option<(Width, u64)> {
+    let (width, "const") = Width::split(&instruction.opcode)? else { return None };
+    let value = match instruction.operands.as_slice() {
+        [Operand::Immediate(value)] => *value as u64,
+        [Operand::LargeImmediate(value)] => *value,
+        _ => return None,
+    };
+    // Negative i32 constants arrive sign-extended to 64 bits
+    Some((width, width.truncate(value)))
+}
+
+fn const_instruction(width: Width, value: u64) -> TranspiledInstruction {
+    let operand = if value <= u32::MAX as u64 {
+        Operand::immediate(value as u32)
+    } else {
+        Operand::large_immediate(value)
+    };
+    TranspiledInstruction::new(format!("{}.const", width.prefix()), vec![operand])
+}
+
+fn is_one_of(instruction: &TranspiledInstruction, opcodes: &[&str]) -> bool {
+    opcodes.contains(&instruction.opcode.as_str())
+}
+
+/// Evaluate a binary integer operation the way the VM would
+fn evaluate(op: &str, width: Width, a: u64, b: u64) -> Option<u64> {
+    let shift = (b % width.bits() as u64) as u32;
+    let value = match op {
+        "add" => a.wrapping_add(b),
+        "sub" => a.wrapping_sub(b),
+        "mul" => a.wrapping_mul(b),
+        "and" => a & b,
+        "or" => a | b,
+        "xor" => a ^ b,
+        "shl" => a.wrapping_shl(shift),
+        "shr_u" => a >> shift,
+        "shr_s" => match width {
+            Width::I32 => ((a as u32 as i32) >> shift) as u32 as u64,
+            Width::I64 => ((a as i64) >> shift) as u64,
+        },
+        // Division can trap, so it is left for run time
+        _ => return None,
+    };
+    Some(width.truncate(value))
+}
+
+/// `const a; const b; op` -> `const (a op b)`
+fn fold_constants(window: &[TranspiledInstruction]) -> Option<Rewrite> {
+    let [a, b, op, ..] = window else { return None };
+    let ((a_width, a), (b_width, b)) = (constant(a)?, constant(b)?);
+    let (width, op) = Width::split(&op.opcode)?;
+    if a_width != width || b_width != width {
+        return None;
+    }
+    let value = evaluate(op, width, a, b)?;
+    Some(Rewrite::new(3, vec![const_instruction(width, value)]))
+}
+
+/// `const x; drop` -> nothing
+fn push_pop(window: &[TranspiledInstruction]) -> Option<Rewrite> {
+    let [push, pop, ..] = window else { return None };
+    (is_one_of(push, PURE_PUSH_OPCODES) && is_one_of(pop, POP_OPCODES)).then(|| Rewrite::new(2, vec![]))
+}
+
+/// `dup; drop` -> nothing, `local.tee n; drop` -> `local.set n`
+fn dup_drop(window: &[TranspiledInstruction]) -> Option<Rewrite> {
+    let [first, pop, ..] = window else { return None };
+    if !is_one_of(pop, POP_OPCODES) {
+        return None;
+    }
+    if first.opcode.eq_ignore_ascii_case("dup") {
+        return Some(Rewrite::new(2, vec![]));
+    }
+    (first.opcode == "local.tee").then(|| Rewrite::new(2, vec![TranspiledInstruction::new("local.set".to_string(), first.operands.clone())]))
+}
+
+/// `local.set n; local.get n` -> `local.tee n`, `global.set n; global.get n` -> `dup; global.set n`
+fn load_after_store(window: &[TranspiledInstruction]) -> Option<Rewrite> {
+    let [store, load, ..] = window else { return None };
+    if store.operands != load.operands {
+        return None;
+    }
+    match (store.opcode.as_str(), load.opcode.as_str()) {
+        ("local.set", "local.get") => Some(Rewrite::new(2, vec![TranspiledInstruction::new("local.tee".to_string(), store.operands.clone())])),
+        ("global.set", "global.get") => Some(Rewrite::new(
+            2,
+            vec![
+                TranspiledInstruction::new("dup".to_string(), vec![]),
+                TranspiledInstruction::new("global.set".to_string(), store.operands.clone()),
+            ],
+        )),
+        _ => None,
+    }
+}
+
+/// `br L` immediately followed by `L:` -> nothing
+fn jump_to_next(window: &[TranspiledInstruction]) -> Option<Rewrite> {
+    let [jump, next, ..] = window else { return None };
+    let [Operand::Label(target)] = jump.operands.as_slice() else { return None };
+    (is_one_of(jump, JUMP_OPCODES) && next.label.as_ref() == Some(target)).then(|| Rewrite::new(1, vec![]))
+}
+
+/// `const 2^k; mul` -> `const k; shl`, and `const 1; mul` -> nothing
+fn strength_reduce(window: &[TranspiledInstruction], target_arch: VmArchitecture) -> Option<Rewrite> {
+    let [factor, mul, ..] = window else { return None };
+    let (width, factor) = constant(factor)?;
+    if Width::split(&mul.opcode)? != (width, "mul") || !factor.is_power_of_two() {
+        return None;
+    }
+    let shift = factor.trailing_zeros() as u64;
+    if shift == 0 {
+        return Some(Rewrite::new(2, vec![]));
+    }
+    if !supports_shift(target_arch, width) {
+        return None;
+    }
+    Some(Rewrite::new(
+        2,
+        vec![const_instruction(width, shift), TranspiledInstruction::new(format!("{}.shl", width.prefix()), vec![])],
+    ))
+}
+
+/// Shifts are native when the operand fits the architecture's word
+fn supports_shift(target_arch: VmArchitecture, width: Width) -> bool {
+    target_arch.word_size() * 8 >= width.bits() as usize
+}
@@ -73,6 +73,11 @@ impl ParseError {
         matches!(self.kind, ParseErrorKind::InternalError | ParseErrorKind::RecursionLimitExceeded | ParseErrorKind::OutOfMemory)
     }
 
+    /// Check if this is a warning that does not fail parsing
+    pub fn is_warning(&self) -> bool {
+        self.kind.severity() == 1
+    }
+
     /// Get a user-friendly error message
     pub fn user_message(&self) -> String {
         format!("{} at line {}, column {}: {}", self.kind.description(), self.position.line, self.position.column, self.message)
@@ -185,6 +190,18 @@ pub enum ParseErrorKind {
     #[error("Invalid operation")]
     InvalidOperation,
 
+    /// Call with the wrong number of arguments
+    #[error("Argument count mismatch")]
+    ArgumentCountMismatch,
+
+    /// Variable that is declared but never read (warning)
+    #[error("Unused variable")]
+    UnusedVariable,
+
+    /// Statement that can never execute (warning)
+    #[error("Unreachable code")]
+    UnreachableCode,
+
     /// Recursion limit exceeded
     #[error("Recursion limit exceeded")]
     RecursionLimitExceeded,
@@ -227,6 +244,9 @@ impl ParseErrorKind {
             ParseErrorKind::RecursionLimitExceeded => "E019",
             ParseErrorKind::OutOfMemory => "E020",
             ParseErrorKind::InternalError => "E021",
+            ParseErrorKind::ArgumentCountMismatch => "E022",
+            ParseErrorKind::UnusedVariable => "W001",
+            ParseErrorKind::UnreachableCode => "W002",
             ParseErrorKind::Custom(_) => "E999",
         }
     }
@@ -255,6 +275,9 @@ impl ParseErrorKind {
             ParseErrorKind::RecursionLimitExceeded => "Recursion limit exceeded",
             ParseErrorKind::OutOfMemory => "Out of memory",
             ParseErrorKind::InternalError => "Internal compiler error",
+            ParseErrorKind::ArgumentCountMismatch => "Argument count mismatch",
+            ParseErrorKind::UnusedVariable => "Unused variable",
+            ParseErrorKind::UnreachableCode => "Unreachable code",
             ParseErrorKind::Custom(_) => "Custom error",
         }
     }
@@ -280,7 +303,10 @@ impl ParseErrorKind {
             | ParseErrorKind::UndefinedSymbol
             | ParseErrorKind::TypeMismatch
             | ParseErrorKind::InvalidAssignment
-            | ParseErrorKind::InvalidOperation => 2, // Error
+            | ParseErrorKind::InvalidOperation
+            | ParseErrorKind::ArgumentCountMismatch => 2, // Error
+
+            ParseErrorKind::UnusedVariable | ParseErrorKind::UnreachableCode => 1, // Warning
 
             ParseErrorKind::RecursionLimitExceeded | ParseErrorKind::OutOfMemory | ParseErrorKind::InternalError => 3, // Fatal
 
@@ -360,6 +386,7 @@ mod tests {
         assert_eq!(ParseErrorKind::SyntaxError.severity(), 2);
         assert_eq!(ParseErrorKind::InternalError.severity(), 3);
         assert_eq!(ParseErrorKind::TypeMismatch.severity(), 2);
+        assert_eq!(ParseErrorKind::UnusedVariable.severity(), 1);
     }
 
     #[test]
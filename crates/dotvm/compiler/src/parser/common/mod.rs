@@ -237,6 +237,7 @@ pub mod utils {
                     Some('t') => result.push('\t'),
                     Some('\\') => result.push('\\'),
                     Some('"') => result.push('"'),
+                    Some('\'') => result.push('\''),
                     Some('u') => {
                         // Unicode escape sequence
                         if chars.next() != Some('{') {
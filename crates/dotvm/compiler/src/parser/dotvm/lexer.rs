@@ -17,8 +17,9 @@
 //! DotVM lexical analyzer (tokenizer)
 
 use crate::parser::common::{
-    ParseError, ParseErrorKind, ParseResult, Position, Token, TokenType,
-    Keyword, Operator, Delimiter, position::{Span, PositionTracker}, utils,
+    Delimiter, Keyword, Operator, ParseError, ParseErrorKind, ParseResult, Position, Token, TokenType,
+    position::{PositionTracker, Span},
+    utils,
 };
 use crate::parser::traits::Lexer;
 
@@ -49,39 +50,40 @@ impl DotVMLexer {
         self.current_token = None;
 
         let mut tokens = Vec::new();
-        
+
         loop {
             let token = self.next_token()?;
             let is_eof = matches!(token.token_type, TokenType::Eof);
             tokens.push(token);
-            
+
             if is_eof {
                 break;
             }
         }
-        
+
         Ok(tokens)
     }
 
+    /// Check if the whole input has been consumed
+    pub fn is_at_end(&self) -> bool {
+        self.tracker.as_ref().is_none_or(|tracker| tracker.is_at_end())
+    }
+
     /// Scan the next token from input
     fn scan_token(&mut self) -> ParseResult<Token> {
         let tracker = self.tracker.as_mut().unwrap();
-        
+
         // Skip whitespace
         tracker.skip_whitespace();
-        
+
         let start_pos = tracker.position();
-        
+
         if tracker.is_at_end() {
-            return Ok(Token::new(
-                TokenType::Eof,
-                "".to_string(),
-                Span::single(start_pos),
-            ));
+            return Ok(Token::new(TokenType::Eof, "".to_string(), Span::single(start_pos)));
         }
 
         let ch = tracker.next_char().unwrap();
-        
+
         match ch {
             // Single-character tokens
             '(' => self.make_token(TokenType::Delimiter(Delimiter::LeftParen), ch.to_string(), start_pos),
@@ -92,7 +94,7 @@ impl DotVMLexer {
             '}' => self.make_token(TokenType::Delimiter(Delimiter::RightBrace), ch.to_string(), start_pos),
             ',' => self.make_token(TokenType::Delimiter(Delimiter::Comma), ch.to_string(), start_pos),
             ';' => self.make_token(TokenType::Delimiter(Delimiter::Semicolon), ch.to_string(), start_pos),
-            
+
             // Operators (may be multi-character)
             '+' => self.scan_operator_starting_with('+', start_pos),
             '-' => self.scan_operator_starting_with('-', start_pos),
@@ -108,41 +110,33 @@ impl DotVMLexer {
             '^' => self.make_token(TokenType::Operator(Operator::BitXor), ch.to_string(), start_pos),
             '~' => self.make_token(TokenType::Operator(Operator::BitNot), ch.to_string(), start_pos),
             '?' => self.make_token(TokenType::Operator(Operator::Question), ch.to_string(), start_pos),
-            
+
             // Dot and ranges
             '.' => self.scan_dot_or_range(start_pos),
-            
+
             // Colon
             ':' => self.scan_colon(start_pos),
-            
+
             // String literals
             '"' => self.scan_string_literal(start_pos),
             '\'' => self.scan_char_literal(start_pos),
-            
+
             // Numbers
             c if c.is_ascii_digit() => {
                 // Put the character back and scan number
-                let mut pos = tracker.position();
-                pos.column -= 1;
                 tracker.seek_to(tracker.byte_offset() - ch.len_utf8()).unwrap();
                 self.scan_number(start_pos)
             }
-            
+
             // Identifiers and keywords
             c if utils::is_identifier_start(c) => {
                 // Put the character back and scan identifier
-                let mut pos = tracker.position();
-                pos.column -= 1;
                 tracker.seek_to(tracker.byte_offset() - ch.len_utf8()).unwrap();
                 self.scan_identifier_or_keyword(start_pos)
             }
-            
+
             // Invalid character
-            _ => Err(ParseError::new(
-                ParseErrorKind::InvalidCharacter,
-                start_pos,
-                format!("Unexpected character '{}'", ch),
-            )),
+            _ => Err(ParseError::new(ParseErrorKind::InvalidCharacter, start_pos, format!("Unexpected character '{}'", ch))),
         }
     }
 
@@ -158,7 +152,7 @@ impl DotVMLexer {
     fn scan_operator_starting_with(&mut self, first_char: char, start_pos: Position) -> ParseResult<Token> {
         let tracker = self.tracker.as_mut().unwrap();
         let mut lexeme = first_char.to_string();
-        
+
         let token_type = match first_char {
             '+' => {
                 if tracker.peek_char() == Some('=') {
@@ -169,36 +163,32 @@ impl DotVMLexer {
                     TokenType::Operator(Operator::Plus)
                 }
             }
-            '-' => {
-                match tracker.peek_char() {
-                    Some('=') => {
-                        tracker.next_char();
-                        lexeme.push('=');
-                        TokenType::Operator(Operator::MinusAssign)
-                    }
-                    Some('>') => {
-                        tracker.next_char();
-                        lexeme.push('>');
-                        TokenType::Operator(Operator::Arrow)
-                    }
-                    _ => TokenType::Operator(Operator::Minus),
+            '-' => match tracker.peek_char() {
+                Some('=') => {
+                    tracker.next_char();
+                    lexeme.push('=');
+                    TokenType::Operator(Operator::MinusAssign)
                 }
-            }
-            '*' => {
-                match tracker.peek_char() {
-                    Some('=') => {
-                        tracker.next_char();
-                        lexeme.push('=');
-                        TokenType::Operator(Operator::MultiplyAssign)
-                    }
-                    Some('*') => {
-                        tracker.next_char();
-                        lexeme.push('*');
-                        TokenType::Operator(Operator::Power)
-                    }
-                    _ => TokenType::Operator(Operator::Multiply),
+                Some('>') => {
+                    tracker.next_char();
+                    lexeme.push('>');
+                    TokenType::Operator(Operator::Arrow)
                 }
-            }
+                _ => TokenType::Operator(Operator::Minus),
+            },
+            '*' => match tracker.peek_char() {
+                Some('=') => {
+                    tracker.next_char();
+                    lexeme.push('=');
+                    TokenType::Operator(Operator::MultiplyAssign)
+                }
+                Some('*') => {
+                    tracker.next_char();
+                    lexeme.push('*');
+                    TokenType::Operator(Operator::Power)
+                }
+                _ => TokenType::Operator(Operator::Multiply),
+            },
             '%' => {
                 if tracker.peek_char() == Some('=') {
                     tracker.next_char();
@@ -208,21 +198,19 @@ impl DotVMLexer {
                     TokenType::Operator(Operator::Modulo)
                 }
             }
-            '=' => {
-                match tracker.peek_char() {
-                    Some('=') => {
-                        tracker.next_char();
-                        lexeme.push('=');
-                        TokenType::Operator(Operator::Equal)
-                    }
-                    Some('>') => {
-                        tracker.next_char();
-                        lexeme.push('>');
-                        TokenType::Operator(Operator::FatArrow)
-                    }
-                    _ => TokenType::Operator(Operator::Assign),
+            '=' => match tracker.peek_char() {
+                Some('=') => {
+                    tracker.next_char();
+                    lexeme.push('=');
+                    TokenType::Operator(Operator::Equal)
                 }
-            }
+                Some('>') => {
+                    tracker.next_char();
+                    lexeme.push('>');
+                    TokenType::Operator(Operator::FatArrow)
+                }
+                _ => TokenType::Operator(Operator::Assign),
+            },
             '!' => {
                 if tracker.peek_char() == Some('=') {
                     tracker.next_char();
@@ -232,36 +220,32 @@ impl DotVMLexer {
                     TokenType::Operator(Operator::Not)
                 }
             }
-            '<' => {
-                match tracker.peek_char() {
-                    Some('=') => {
-                        tracker.next_char();
-                        lexeme.push('=');
-                        TokenType::Operator(Operator::LessEqual)
-                    }
-                    Some('<') => {
-                        tracker.next_char();
-                        lexeme.push('<');
-                        TokenType::Operator(Operator::LeftShift)
-                    }
-                    _ => TokenType::Operator(Operator::Less),
+            '<' => match tracker.peek_char() {
+                Some('=') => {
+                    tracker.next_char();
+                    lexeme.push('=');
+                    TokenType::Operator(Operator::LessEqual)
                 }
-            }
-            '>' => {
-                match tracker.peek_char() {
-                    Some('=') => {
-                        tracker.next_char();
-                        lexeme.push('=');
-                        TokenType::Operator(Operator::GreaterEqual)
-                    }
-                    Some('>') => {
-                        tracker.next_char();
-                        lexeme.push('>');
-                        TokenType::Operator(Operator::RightShift)
-                    }
-                    _ => TokenType::Operator(Operator::Greater),
+                Some('<') => {
+                    tracker.next_char();
+                    lexeme.push('<');
+                    TokenType::Operator(Operator::LeftShift)
                 }
-            }
+                _ => TokenType::Operator(Operator::Less),
+            },
+            '>' => match tracker.peek_char() {
+                Some('=') => {
+                    tracker.next_char();
+                    lexeme.push('=');
+                    TokenType::Operator(Operator::GreaterEqual)
+                }
+                Some('>') => {
+                    tracker.next_char();
+                    lexeme.push('>');
+                    TokenType::Operator(Operator::RightShift)
+                }
+                _ => TokenType::Operator(Operator::Greater),
+            },
             '&' => {
                 if tracker.peek_char() == Some('&') {
                     tracker.next_char();
@@ -282,39 +266,40 @@ impl DotVMLexer {
             }
             _ => unreachable!(),
         };
-        
+
         self.make_token(token_type, lexeme, start_pos)
     }
 
     /// Scan slash or comment
     fn scan_slash_or_comment(&mut self, start_pos: Position) -> ParseResult<Token> {
         let tracker = self.tracker.as_mut().unwrap();
-        
+
         match tracker.peek_char() {
             Some('/') => {
                 // Line comment
                 tracker.next_char(); // consume second '/'
                 let mut comment = String::new();
-                
+
                 while let Some(ch) = tracker.peek_char() {
                     if ch == '\n' {
                         break;
                     }
                     comment.push(tracker.next_char().unwrap());
                 }
-                
-                self.make_token(TokenType::Comment(comment), format!("//{}", comment), start_pos)
+
+                let lexeme = format!("//{}", comment);
+                self.make_token(TokenType::Comment(comment), lexeme, start_pos)
             }
             Some('*') => {
                 // Block comment
                 tracker.next_char(); // consume '*'
                 let mut comment = String::new();
                 let mut depth = 1;
-                
+
                 while depth > 0 && !tracker.is_at_end() {
                     let ch = tracker.next_char().unwrap();
                     comment.push(ch);
-                    
+
                     if ch == '/' && tracker.peek_char() == Some('*') {
                         tracker.next_char();
                         comment.push('*');
@@ -325,16 +310,15 @@ impl DotVMLexer {
                         depth -= 1;
                     }
                 }
-                
+
                 if depth > 0 {
-                    return Err(ParseError::new(
-                        ParseErrorKind::UnterminatedComment,
-                        start_pos,
-                        "Unterminated block comment".to_string(),
-                    ));
+                    return Err(ParseError::new(ParseErrorKind::UnterminatedComment, start_pos, "Unterminated block comment".to_string()));
                 }
-                
-                self.make_token(TokenType::Comment(comment), format!("/*{}*/", comment), start_pos)
+
+                // Drop the closing "*/" that ended the loop
+                comment.truncate(comment.len() - 2);
+                let lexeme = format!("/*{}*/", comment);
+                self.make_token(TokenType::Comment(comment), lexeme, start_pos)
             }
             Some('=') => {
                 // /= operator
@@ -351,7 +335,7 @@ impl DotVMLexer {
     /// Scan dot or range operators
     fn scan_dot_or_range(&mut self, start_pos: Position) -> ParseResult<Token> {
         let tracker = self.tracker.as_mut().unwrap();
-        
+
         match tracker.peek_char() {
             Some('.') => {
                 tracker.next_char();
@@ -369,7 +353,7 @@ impl DotVMLexer {
     /// Scan colon or double colon
     fn scan_colon(&mut self, start_pos: Position) -> ParseResult<Token> {
         let tracker = self.tracker.as_mut().unwrap();
-        
+
         if tracker.peek_char() == Some(':') {
             tracker.next_char();
             self.make_token(TokenType::Operator(Operator::DoubleColon), "::".to_string(), start_pos)
@@ -378,114 +362,62 @@ impl DotVMLexer {
         }
     }
 
-    /// Scan string literal
-    fn scan_string_literal(&mut self, start_pos: Position) -> ParseResult<Token> {
+    /// Scan the raw text of a quoted literal up to the closing `quote`
+    ///
+    /// Escape sequences are kept as written so they can be decoded by
+    /// [`utils::unescape_string`]; an escaped quote does not end the literal.
+    fn scan_quoted(&mut self, quote: char, start_pos: Position) -> ParseResult<String> {
         let tracker = self.tracker.as_mut().unwrap();
-        let mut value = String::new();
-        let mut lexeme = String::from("\"");
-        
-        while let Some(ch) = tracker.peek_char() {
-            if ch == '"' {
-                tracker.next_char();
-                lexeme.push('"');
-                break;
-            } else if ch == '\\' {
-                tracker.next_char();
-                lexeme.push('\\');
-                
-                if let Some(escaped) = tracker.next_char() {
-                    lexeme.push(escaped);
-                    match escaped {
-                        'n' => value.push('\n'),
-                        'r' => value.push('\r'),
-                        't' => value.push('\t'),
-                        '\\' => value.push('\\'),
-                        '"' => value.push('"'),
-                        _ => {
-                            return Err(ParseError::new(
-                                ParseErrorKind::InvalidEscapeSequence,
-                                tracker.position(),
-                                format!("Invalid escape sequence: \\{}", escaped),
-                            ));
-                        }
+        let mut raw = String::new();
+
+        loop {
+            match tracker.next_char() {
+                Some(ch) if ch == quote => return Ok(raw),
+                Some('\\') => {
+                    raw.push('\\');
+                    match tracker.next_char() {
+                        Some(escaped) => raw.push(escaped),
+                        None => break,
                     }
-                } else {
-                    return Err(ParseError::new(
-                        ParseErrorKind::UnterminatedString,
-                        start_pos,
-                        "Unterminated string literal".to_string(),
-                    ));
                 }
-            } else {
-                value.push(tracker.next_char().unwrap());
-                lexeme.push(ch);
+                Some('\n') if quote == '\'' => break,
+                Some(ch) => raw.push(ch),
+                None => break,
             }
         }
-        
-        if !lexeme.ends_with('"') {
-            return Err(ParseError::new(
-                ParseErrorKind::UnterminatedString,
-                start_pos,
-                "Unterminated string literal".to_string(),
-            ));
-        }
-        
-        self.make_token(TokenType::StringLiteral(value), lexeme, start_pos)
+
+        let message = if quote == '"' { "Unterminated string literal" } else { "Unterminated character literal" };
+        Err(ParseError::new(ParseErrorKind::UnterminatedString, start_pos, message.to_string()))
+    }
+
+    /// Decode the escape sequences of a literal's raw text
+    fn unescape(raw: &str, start_pos: Position) -> ParseResult<String> {
+        // `unescape_string` has no position information; report the literal instead
+        utils::unescape_string(raw).map_err(|error| ParseError::new(error.kind, start_pos, error.message))
+    }
+
+    /// Scan string literal
+    fn scan_string_literal(&mut self, start_pos: Position) -> ParseResult<Token> {
+        let raw = self.scan_quoted('"', start_pos)?;
+        let value = Self::unescape(&raw, start_pos)?;
+        self.make_token(TokenType::StringLiteral(value), format!("\"{}\"", raw), start_pos)
     }
 
     /// Scan character literal
     fn scan_char_literal(&mut self, start_pos: Position) -> ParseResult<Token> {
-        let tracker = self.tracker.as_mut().unwrap();
-        let mut lexeme = String::from("'");
-        
-        let ch = if let Some(ch) = tracker.next_char() {
-            lexeme.push(ch);
-            if ch == '\\' {
-                // Escaped character
-                if let Some(escaped) = tracker.next_char() {
-                    lexeme.push(escaped);
-                    match escaped {
-                        'n' => '\n',
-                        'r' => '\r',
-                        't' => '\t',
-                        '\\' => '\\',
-                        '\'' => '\'',
-                        _ => {
-                            return Err(ParseError::new(
-                                ParseErrorKind::InvalidEscapeSequence,
-                                tracker.position(),
-                                format!("Invalid escape sequence: \\{}", escaped),
-                            ));
-                        }
-                    }
-                } else {
-                    return Err(ParseError::new(
-                        ParseErrorKind::InvalidCharacter,
-                        start_pos,
-                        "Incomplete character literal".to_string(),
-                    ));
-                }
-            } else {
-                ch
-            }
-        } else {
-            return Err(ParseError::new(
-                ParseErrorKind::InvalidCharacter,
-                start_pos,
-                "Empty character literal".to_string(),
-            ));
-        };
-        
-        if tracker.next_char() != Some('\'') {
-            return Err(ParseError::new(
+        let raw = self.scan_quoted('\'', start_pos)?;
+        let value = Self::unescape(&raw, start_pos)?;
+
+        let mut chars = value.chars();
+        match (chars.next(), chars.next()) {
+            (Some(ch), None) => self.make_token(TokenType::CharLiteral(ch), format!("'{}'", raw), start_pos),
+            (None, _) => Err(ParseError::new(ParseErrorKind::InvalidCharacter, start_pos, "Empty character literal".to_string())),
+            _ => Err(ParseError::new(
                 ParseErrorKind::InvalidCharacter,
                 start_pos,
-                "Unterminated character literal".to_string(),
-            ));
+                format!("Character literal '{}' contains more than one character", raw),
+            )),
         }
-        lexeme.push('\'');
-        
-        self.make_token(TokenType::CharLiteral(ch), lexeme, start_pos)
     }
 
     /// Scan number literal
@@ -493,7 +425,7 @@ impl DotVMLexer {
         let tracker = self.tracker.as_mut().unwrap();
         let mut lexeme = String::new();
         let mut is_float = false;
-        
+
         // Scan integer part
         while let Some(ch) = tracker.peek_char() {
             if ch.is_ascii_digit() {
@@ -502,7 +434,7 @@ impl DotVMLexer {
                 break;
             }
         }
-        
+
         // Check for decimal point
         if tracker.peek_char() == Some('.') {
             // Look ahead to see if it's a decimal or range operator
@@ -510,7 +442,7 @@ impl DotVMLexer {
             if next_chars.len() >= 2 && next_chars.chars().nth(1).unwrap().is_ascii_digit() {
                 is_float = true;
                 lexeme.push(tracker.next_char().unwrap()); // consume '.'
-                
+
                 // Scan fractional part
                 while let Some(ch) = tracker.peek_char() {
                     if ch.is_ascii_digit() {
@@ -521,65 +453,49 @@ impl DotVMLexer {
                 }
             }
         }
-        
+
         // Check for scientific notation
-        if let Some(ch) = tracker.peek_char() {
-            if ch == 'e' || ch == 'E' {
-                is_float = true;
+        if matches!(tracker.peek_char(), Some('e' | 'E')) {
+            is_float = true;
+            lexeme.push(tracker.next_char().unwrap());
+
+            // Optional sign
+            if matches!(tracker.peek_char(), Some('+' | '-')) {
                 lexeme.push(tracker.next_char().unwrap());
-                
-                // Optional sign
-                if let Some(sign) = tracker.peek_char() {
-                    if sign == '+' || sign == '-' {
-                        lexeme.push(tracker.next_char().unwrap());
-                    }
-                }
-                
-                // Exponent digits
-                let mut has_exponent_digits = false;
-                while let Some(ch) = tracker.peek_char() {
-                    if ch.is_ascii_digit() {
-                        lexeme.push(tracker.next_char().unwrap());
-                        has_exponent_digits = true;
-                    } else {
-                        break;
-                    }
-                }
-                
-                if !has_exponent_digits {
-                    return Err(ParseError::new(
-                        ParseErrorKind::InvalidNumber,
-                        start_pos,
-                        "Invalid number: missing exponent digits".to_string(),
-                    ));
+            }
+
+            // Exponent digits
+            let mut has_exponent_digits = false;
+            while let Some(ch) = tracker.peek_char() {
+                if ch.is_ascii_digit() {
+                    lexeme.push(tracker.next_char().unwrap());
+                    has_exponent_digits = true;
+                } else {
+                    break;
                 }
             }
+
+            if !has_exponent_digits {
+                return Err(ParseError::new(ParseErrorKind::InvalidNumber, start_pos, "Invalid number: missing exponent digits".to_string()));
+            }
         }
-        
+
         let token_type = if is_float {
             match lexeme.parse::<f64>() {
                 Ok(value) => TokenType::FloatLiteral(value),
                 Err(_) => {
-                    return Err(ParseError::new(
-                        ParseErrorKind::InvalidNumber,
-                        start_pos,
-                        format!("Invalid float literal: {}", lexeme),
-                    ));
+                    return Err(ParseError::new(ParseErrorKind::InvalidNumber, start_pos, format!("Invalid float literal: {}", lexeme)));
                 }
             }
         } else {
             match lexeme.parse::<i64>() {
                 Ok(value) => TokenType::IntegerLiteral(value),
                 Err(_) => {
-                    return Err(ParseError::new(
-                        ParseErrorKind::InvalidNumber,
-                        start_pos,
-                        format!("Invalid integer literal: {}", lexeme),
-                    ));
+                    return Err(ParseError::new(ParseErrorKind::InvalidNumber, start_pos, format!("Invalid integer literal: {}", lexeme)));
                 }
             }
         };
-        
+
         self.make_token(token_type, lexeme, start_pos)
     }
 
@@ -587,12 +503,12 @@ impl DotVMLexer {
     fn scan_identifier_or_keyword(&mut self, start_pos: Position) -> ParseResult<Token> {
         let tracker = self.tracker.as_mut().unwrap();
         let mut lexeme = String::new();
-        
+
         // First character (already validated as identifier start)
         if let Some(ch) = tracker.next_char() {
             lexeme.push(ch);
         }
-        
+
         // Remaining characters
         while let Some(ch) = tracker.peek_char() {
             if utils::is_identifier_continue(ch) {
@@ -601,7 +517,7 @@ impl DotVMLexer {
                 break;
             }
         }
-        
+
         let token_type = if let Some(keyword) = Keyword::from_str(&lexeme) {
             match keyword {
                 Keyword::True => TokenType::BooleanLiteral(true),
@@ -611,7 +527,7 @@ impl DotVMLexer {
         } else {
             TokenType::Identifier(lexeme.clone())
         };
-        
+
         self.make_token(token_type, lexeme, start_pos)
     }
 }
@@ -621,17 +537,17 @@ impl Lexer for DotVMLexer {
         if let Some(token) = self.current_token.take() {
             return Ok(token);
         }
-        
+
         if self.at_end {
             let pos = self.tracker.as_ref().map_or(Position::start(), |t| t.position());
             return Ok(Token::new(TokenType::Eof, "".to_string(), Span::single(pos)));
         }
-        
+
         let token = self.scan_token()?;
         if matches!(token.token_type, TokenType::Eof) {
             self.at_end = true;
         }
-        
+
         Ok(token)
     }
 
@@ -647,7 +563,7 @@ impl Lexer for DotVMLexer {
     }
 
     fn has_more_tokens(&self) -> bool {
-        !self.at_end
+        self.tracker.is_some() && !self.at_end
     }
 
     fn position(&self) -> Position {
@@ -698,7 +614,7 @@ mod tests {
     fn test_tokenize_simple_tokens() {
         let mut lexer = DotVMLexer::new();
         let tokens = lexer.tokenize("( ) [ ] { } , ;").unwrap();
-        
+
         assert_eq!(tokens.len(), 9); // 8 delimiters + EOF
         assert!(matches!(tokens[0].token_type, TokenType::Delimiter(Delimiter::LeftParen)));
         assert!(matches!(tokens[1].token_type, TokenType::Delimiter(Delimiter::RightParen)));
@@ -710,7 +626,7 @@ mod tests {
     fn test_tokenize_operators() {
         let mut lexer = DotVMLexer::new();
         let tokens = lexer.tokenize("+ - * / % == != <= >= && ||").unwrap();
-        
+
         // Should have operators + EOF
         assert!(tokens.len() > 10);
         assert!(matches!(tokens[0].token_type, TokenType::Operator(Operator::Plus)));
@@ -722,7 +638,7 @@ mod tests {
     fn test_tokenize_numbers() {
         let mut lexer = DotVMLexer::new();
         let tokens = lexer.tokenize("42 3.14 1e10").unwrap();
-        
+
         assert_eq!(tokens.len(), 4); // 3 numbers + EOF
         assert!(matches!(tokens[0].token_type, TokenType::IntegerLiteral(42)));
         assert!(matches!(tokens[1].token_type, TokenType::FloatLiteral(_)));
@@ -733,7 +649,7 @@ mod tests {
     fn test_tokenize_strings() {
         let mut lexer = DotVMLexer::new();
         let tokens = lexer.tokenize(r#""hello" "world\n""#).unwrap();
-        
+
         assert_eq!(tokens.len(), 3); // 2 strings + EOF
         if let TokenType::StringLiteral(s) = &tokens[0].token_type {
             assert_eq!(s, "hello");
@@ -742,11 +658,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_tokenize_escapes() {
+        let mut lexer = DotVMLexer::new();
+        let tokens = lexer.tokenize(r#""say \"hi\"\u{21}" '\n' '\'' 'x'"#).unwrap();
+
+        assert_eq!(tokens[0].token_type, TokenType::StringLiteral("say \"hi\"!".to_string()));
+        assert_eq!(tokens[0].lexeme, r#""say \"hi\"\u{21}""#);
+        assert_eq!(tokens[1].token_type, TokenType::CharLiteral('\n'));
+        assert_eq!(tokens[2].token_type, TokenType::CharLiteral('\''));
+        assert_eq!(tokens[3].token_type, TokenType::CharLiteral('x'));
+
+        // Escape errors point at the literal
+        let error = lexer.tokenize("let s = \"bad \\q\";").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::InvalidEscapeSequence);
+        assert_eq!(error.position, Position::new(1, 9));
+
+        assert!(lexer.tokenize("'ab'").is_err());
+        assert!(lexer.tokenize("''").is_err());
+    }
+
     #[test]
     fn test_tokenize_identifiers_and_keywords() {
         let mut lexer = DotVMLexer::new();
         let tokens = lexer.tokenize("let x if true false").unwrap();
-        
+
         assert_eq!(tokens.len(), 6); // 5 tokens + EOF
         assert!(matches!(tokens[0].token_type, TokenType::Keyword(Keyword::Let)));
         assert!(matches!(tokens[1].token_type, TokenType::Identifier(_)));
@@ -759,7 +695,7 @@ mod tests {
     fn test_tokenize_comments() {
         let mut lexer = DotVMLexer::new();
         let tokens = lexer.tokenize("// line comment\n/* block comment */").unwrap();
-        
+
         assert_eq!(tokens.len(), 3); // 2 comments + EOF
         assert!(matches!(tokens[0].token_type, TokenType::Comment(_)));
         assert!(matches!(tokens[1].token_type, TokenType::Comment(_)));
@@ -768,13 +704,13 @@ mod tests {
     #[test]
     fn test_error_handling() {
         let mut lexer = DotVMLexer::new();
-        
+
         // Unterminated string
         let result = lexer.tokenize(r#""unterminated"#);
         assert!(result.is_err());
-        
+
         // Invalid character
         let result = lexer.tokenize("@");
         assert!(result.is_err());
     }
-}
\ No newline at end of file
+}
@@ -17,15 +17,15 @@
 //! DotVM-specific parsing implementation
 
 pub mod lexer;
-pub mod syntax_parser;
 pub mod semantic_analyzer;
+pub mod syntax_parser;
 
 pub use lexer::DotVMLexer;
-pub use syntax_parser::{DotVMSyntaxParser, DotVMAst, AstNode};
 pub use semantic_analyzer::DotVMSemanticAnalyzer;
+pub use syntax_parser::{AstNode, DotVMAst, DotVMSyntaxParser, Literal, NodeKind, Parameter, StateOperation};
 
-use super::common::{ParseResult, ParseContext};
-use super::traits::Parser;
+use super::common::{ParseContext, ParseError, ParseResult};
+use super::traits::{Lexer, Parser, SyntaxParser};
 
 /// Main DotVM parser that coordinates lexical, syntax, and semantic analysis
 pub struct DotVMParser {
@@ -40,29 +40,50 @@ impl DotVMParser {
     pub fn new(context: ParseContext) -> Self {
         Self {
             lexer: DotVMLexer::new(),
-            syntax_parser: DotVMSyntaxParser::new(),
-            semantic_analyzer: DotVMSemanticAnalyzer::new(),
+            syntax_parser: DotVMSyntaxParser::with_config(context.config.clone()),
+            semantic_analyzer: DotVMSemanticAnalyzer::with_config(context.config.clone()),
             context,
         }
     }
 
     /// Parse DotVM source code into a validated AST
+    ///
+    /// Errors carry the offending source line as their context.
     pub fn parse_program(&mut self, input: &str) -> ParseResult<DotVMAst> {
+        self.context.source = input.to_string();
+        self.run_phases(input).map_err(|error| self.with_source_context(error))
+    }
+
+    fn run_phases(&mut self, input: &str) -> ParseResult<DotVMAst> {
         // Step 1: Lexical analysis
         let tokens = self.lexer.tokenize(input)?;
-        
+
         // Step 2: Syntax analysis
         let mut ast = self.syntax_parser.parse_tokens(&tokens)?;
-        
+
         // Step 3: Semantic analysis
         self.semantic_analyzer.analyze(&ast)?;
-        
+
         // Step 4: Apply any transformations
         ast = self.apply_transformations(ast)?;
-        
+
         Ok(ast)
     }
 
+    /// Attach the source line of the error, marked at the error column
+    fn with_source_context(&self, error: ParseError) -> ParseError {
+        if error.context.is_some() || !error.position.is_valid() {
+            return error;
+        }
+
+        let mut lines = self.context.get_context(error.position, 0);
+        if lines.is_empty() {
+            return error;
+        }
+        lines.push(format!("{:4} | {}^", "", " ".repeat(error.position.column - 1)));
+        error.with_context(lines.join("\n"))
+    }
+
     /// Apply AST transformations
     fn apply_transformations(&mut self, ast: DotVMAst) -> ParseResult<DotVMAst> {
         // TODO: Implement AST transformations like:
@@ -72,6 +93,11 @@ impl DotVMParser {
         Ok(ast)
     }
 
+    /// Warnings from the semantic analysis of the last parsed program
+    pub fn warnings(&self) -> &[ParseError] {
+        self.semantic_analyzer.warnings()
+    }
+
     /// Get the current parsing context
     pub fn context(&self) -> &ParseContext {
         &self.context
@@ -79,6 +105,8 @@ impl DotVMParser {
 
     /// Update the parsing context
     pub fn set_context(&mut self, context: ParseContext) {
+        self.syntax_parser.set_config(context.config.clone());
+        self.semantic_analyzer.set_config(context.config.clone());
         self.context = context;
     }
 }
@@ -110,7 +138,7 @@ impl Parser<DotVMAst> for DotVMParser {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::parser::common::ParserConfig;
+    use crate::parser::common::{ParseErrorKind, ParserConfig};
 
     #[test]
     fn test_dotvm_parser_creation() {
@@ -123,19 +151,67 @@ mod tests {
     fn test_dotvm_parser_empty_input() {
         let context = ParseContext::new("test.dvm".to_string(), "".to_string());
         let mut parser = DotVMParser::new(context);
-        
+
         // Empty input should parse successfully (empty program)
         let result = parser.parse("");
-        assert!(result.is_ok());
+        assert!(result.unwrap().is_empty());
+        assert!(parser.is_at_end());
     }
 
     #[test]
     fn test_dotvm_parser_simple_program() {
         let context = ParseContext::new("test.dvm".to_string(), "".to_string());
         let mut parser = DotVMParser::new(context);
-        
+
         let input = "let x: i32 = 42;";
-        let result = parser.parse(input);
-        assert!(result.is_ok());
+        let ast = parser.parse(input).unwrap();
+        assert_eq!(ast.items.len(), 1);
+        assert!(matches!(&ast.items[0].kind, NodeKind::Let { name, .. } if name == "x"));
+    }
+
+    #[test]
+    fn test_errors_show_the_offending_line() {
+        let context = ParseContext::new("test.dvm".to_string(), "".to_string());
+        let mut parser = DotVMParser::new(context);
+
+        let error = parser.parse("let x = 1;\nlet y = x + ;\n").unwrap_err();
+        assert_eq!(error.position.line, 2);
+        assert_eq!(error.context.as_deref(), Some("   2 | let y = x + ;\n     |             ^"));
+
+        // Lexical errors get the same treatment
+        let error = parser.parse("let s = \"open").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UnterminatedString);
+        assert!(error.context.unwrap().starts_with("   1 | let s = \"open"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_semantic_diagnostics() {
+        let context = ParseContext::new("test.dvm".to_string(), "".to_string());
+        let mut parser = DotVMParser::new(context);
+
+        let error = parser.parse("let x = 1;\nlet y: bool = x;\n").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::TypeMismatch);
+        assert_eq!(error.context.as_deref(), Some("   2 | let y: bool = x;\n     |               ^"));
+
+        // Warnings do not fail the parse
+        assert!(parser.parse("let unused = 1;").is_ok());
+        assert_eq!(parser.warnings().len(), 1);
+        assert_eq!(parser.warnings()[0].kind, ParseErrorKind::UnusedVariable);
+
+        // Lenient type checking follows the context configuration
+        let config = ParserConfig::new().with_strict_types(false);
+        parser.set_context(ParseContext::with_config("test.dvm".to_string(), "".to_string(), config));
+        assert!(parser.parse("let a: i32 = 1;\nlet b: f64 = a;\nlet c = b;").is_ok());
+    }
+
+    #[test]
+    fn test_parser_respects_recursion_limit() {
+        let config = ParserConfig::new().with_max_recursion_depth(8);
+        let context = ParseContext::with_config("test.dvm".to_string(), "".to_string(), config);
+        let mut parser = DotVMParser::new(context);
+
+        let error = parser.parse("let x = ((((((((((1))))))))));").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::RecursionLimitExceeded);
+        assert!(parser.parse("let x = (1);").is_ok());
+    }
+}
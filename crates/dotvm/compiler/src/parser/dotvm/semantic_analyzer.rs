@@ -0,0 +1,957 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! DotVM semantic analysis
+//!
+//! Resolves names against a [`ScopedSymbolTable`] and checks the types of
+//! expressions against declarations, function signatures and operators.
+//! Functions are visible throughout the block that defines them, variables
+//! only after their `let`. Blocks, branches, loop bodies and function bodies
+//! each open a scope, and a name may be declared once per scope.
+//!
+//! Integer literals take the integer type their context asks for and default
+//! to `i32`, float literals default to `f64`. With
+//! [`ParserConfig::strict_type_checking`] disabled, numeric types also convert
+//! into each other implicitly. Expressions whose type cannot be known, such as
+//! `state.load(key)` or field access, are accepted wherever a value is expected.
+//!
+//! All errors found in a program are reported, the first one carrying the rest
+//! as related errors. Unused variables and unreachable code are collected as
+//! warnings and do not fail the analysis.
+
+use super::syntax_parser::{AstNode, DotVMAst, Literal, NodeKind, StateOperation};
+use crate::parser::common::{Operator, ParseError, ParseErrorKind, ParseResult, ParserConfig, Position};
+use crate::parser::traits::{BaseType, FunctionType, SemanticAnalyzer, SymbolInfo, SymbolTable, SymbolType, VariableType};
+use std::collections::HashMap;
+
+/// Name of the custom type recorded for variables whose type could not be inferred
+pub const UNKNOWN_TYPE: &str = "?";
+
+/// A symbol and whether it has been read since it was declared
+#[derive(Debug, Clone)]
+struct Entry {
+    info: SymbolInfo,
+    used: bool,
+}
+
+/// Symbol table with lexical scopes, the outermost being the global scope
+#[derive(Debug)]
+pub struct ScopedSymbolTable {
+    scopes: Vec<HashMap<String, Entry>>,
+}
+
+impl Default for ScopedSymbolTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScopedSymbolTable {
+    /// Create a table holding only the empty global scope
+    pub fn new() -> Self {
+        Self { scopes: vec![HashMap::new()] }
+    }
+
+    /// Declare a symbol in the current scope
+    ///
+    /// Fails with [`ParseErrorKind::DuplicateDeclaration`] if the name is already
+    /// declared in this scope. Shadowing a name from an outer scope is allowed.
+    pub fn declare(&mut self, mut info: SymbolInfo) -> ParseResult<()> {
+        let scope_level = self.scope_level();
+        let scope = self.scopes.last_mut().expect("global scope is never removed");
+
+        if let Some(previous) = scope.get(&info.name) {
+            return Err(ParseError::duplicate_declaration(info.position, info.name.clone()).with_related(ParseError::new(
+                ParseErrorKind::DuplicateDeclaration,
+                previous.info.position,
+                format!("'{}' is first declared here", info.name),
+            )));
+        }
+
+        info.scope_level = scope_level;
+        scope.insert(info.name.clone(), Entry { info, used: false });
+        Ok(())
+    }
+
+    /// Look up a symbol and mark it as used
+    pub fn resolve(&mut self, name: &str) -> Option<&SymbolInfo> {
+        let entry = self.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(name))?;
+        entry.used = true;
+        Some(&entry.info)
+    }
+
+    /// Exit the current scope, returning the symbols that were never used
+    ///
+    /// The global scope is never removed; closing it only reports its unused symbols.
+    pub fn close_scope(&mut self) -> Vec<SymbolInfo> {
+        let unused = self.unused_symbols();
+        if self.scopes.len() > 1 {
+            self.scopes.pop();
+        }
+        unused
+    }
+
+    /// Symbols of the current scope that were never used
+    pub fn unused_symbols(&self) -> Vec<SymbolInfo> {
+        self.scopes
+            .last()
+            .map(|scope| scope.values().filter(|entry| !entry.used).map(|entry| entry.info.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Remove every symbol and scope except an empty global scope
+    pub fn clear(&mut self) {
+        self.scopes = vec![HashMap::new()];
+    }
+}
+
+impl SymbolTable for ScopedSymbolTable {
+    fn define(&mut self, name: String, symbol_type: SymbolType) -> ParseResult<()> {
+        self.declare(SymbolInfo {
+            name,
+            symbol_type,
+            position: Position::unknown(),
+            scope_level: 0,
+            is_mutable: false,
+        })
+    }
+
+    fn lookup(&self, name: &str) -> Option<&SymbolInfo> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name)).map(|entry| &entry.info)
+    }
+
+    fn enter_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn exit_scope(&mut self) {
+        self.close_scope();
+    }
+
+    fn scope_level(&self) -> usize {
+        self.scopes.len() - 1
+    }
+}
+
+/// Type of an expression during analysis
+#[derive(Debug, Clone, PartialEq)]
+enum Type {
+    Known(BaseType),
+    /// Integer literal, fits any integer type
+    IntLiteral,
+    /// Float literal, fits any float type
+    FloatLiteral,
+    /// Could not be determined, accepted everywhere
+    Unknown,
+}
+
+impl Type {
+    fn from_base(base_type: &BaseType) -> Self {
+        match base_type {
+            BaseType::Custom(name) if name == UNKNOWN_TYPE => Type::Unknown,
+            other => Type::Known(other.clone()),
+        }
+    }
+
+    /// Type recorded for a binding of this type
+    fn to_base(&self) -> BaseType {
+        match self {
+            Type::Known(base_type) => base_type.clone(),
+            Type::IntLiteral => BaseType::I32,
+            Type::FloatLiteral => BaseType::F64,
+            Type::Unknown => BaseType::Custom(UNKNOWN_TYPE.to_string()),
+        }
+    }
+
+    fn is_integer(&self) -> bool {
+        match self {
+            Type::Known(base_type) => is_integer(base_type),
+            Type::IntLiteral | Type::Unknown => true,
+            Type::FloatLiteral => false,
+        }
+    }
+
+    fn is_numeric(&self) -> bool {
+        match self {
+            Type::Known(base_type) => numeric_rank(base_type).is_some(),
+            Type::IntLiteral | Type::FloatLiteral | Type::Unknown => true,
+        }
+    }
+
+    fn is(&self, base_type: BaseType) -> bool {
+        matches!(self, Type::Unknown) || *self == Type::Known(base_type)
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Type::Known(base_type) => type_name(base_type),
+            Type::IntLiteral => "integer".to_string(),
+            Type::FloatLiteral => "float".to_string(),
+            Type::Unknown => "unknown".to_string(),
+        }
+    }
+}
+
+fn is_integer(base_type: &BaseType) -> bool {
+    matches!(numeric_rank(base_type), Some(rank) if rank <= 4)
+}
+
+fn is_float(base_type: &BaseType) -> bool {
+    matches!(base_type, BaseType::F32 | BaseType::F64)
+}
+
+/// Width rank of numeric types, floats ranking above all integers
+fn numeric_rank(base_type: &BaseType) -> Option<u8> {
+    match base_type {
+        BaseType::I8 | BaseType::U8 => Some(1),
+        BaseType::I16 | BaseType::U16 => Some(2),
+        BaseType::I32 | BaseType::U32 => Some(3),
+        BaseType::I64 | BaseType::U64 => Some(4),
+        BaseType::F32 => Some(5),
+        BaseType::F64 => Some(6),
+        _ => None,
+    }
+}
+
+/// Source spelling of a type
+fn type_name(base_type: &BaseType) -> String {
+    match base_type {
+        BaseType::I8 => "i8",
+        BaseType::I16 => "i16",
+        BaseType::I32 => "i32",
+        BaseType::I64 => "i64",
+        BaseType::U8 => "u8",
+        BaseType::U16 => "u16",
+        BaseType::U32 => "u32",
+        BaseType::U64 => "u64",
+        BaseType::F32 => "f32",
+        BaseType::F64 => "f64",
+        BaseType::Bool => "bool",
+        BaseType::String => "string",
+        BaseType::Void => "void",
+        BaseType::Custom(name) => name,
+    }
+    .to_string()
+}
+
+fn variable_type(base_type: BaseType) -> VariableType {
+    VariableType {
+        base_type,
+        is_array: false,
+        array_size: None,
+    }
+}
+
+/// DotVM semantic analyzer
+#[derive(Debug)]
+pub struct DotVMSemanticAnalyzer {
+    /// Parser configuration
+    config: ParserConfig,
+    /// Symbols of the program, holding the global scope after analysis
+    symbols: ScopedSymbolTable,
+    /// Errors of the last analysis in source order
+    errors: Vec<ParseError>,
+    /// Warnings of the last analysis in source order
+    warnings: Vec<ParseError>,
+    /// Return types of the enclosing function definitions, innermost last
+    return_types: Vec<BaseType>,
+    /// Number of loops enclosing the current statement within its function
+    loop_depth: usize,
+}
+
+impl Default for DotVMSemanticAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DotVMSemanticAnalyzer {
+    /// Create a new semantic analyzer with the default configuration
+    pub fn new() -> Self {
+        Self::with_config(ParserConfig::default())
+    }
+
+    /// Create a semantic analyzer with a custom configuration
+    pub fn with_config(config: ParserConfig) -> Self {
+        Self {
+            config,
+            symbols: ScopedSymbolTable::new(),
+            errors: Vec::new(),
+            warnings: Vec::new(),
+            return_types: Vec::new(),
+            loop_depth: 0,
+        }
+    }
+
+    /// Get the analyzer configuration
+    pub fn config(&self) -> &ParserConfig {
+        &self.config
+    }
+
+    /// Replace the analyzer configuration
+    pub fn set_config(&mut self, config: ParserConfig) {
+        self.config = config;
+    }
+
+    /// Analyze a parsed program
+    ///
+    /// Fails with the first error, which carries any further errors as related
+    /// errors. Warnings are available from [`Self::warnings`] either way.
+    pub fn analyze(&mut self, ast: &DotVMAst) -> ParseResult<()> {
+        self.reset();
+
+        self.check_block(&ast.items);
+        let unused = self.symbols.unused_symbols();
+        self.warn_unused(unused);
+
+        self.errors.sort_by_key(|error| error.position);
+        self.warnings.sort_by_key(|warning| warning.position);
+
+        match self.errors.split_first() {
+            None => Ok(()),
+            Some((first, rest)) => Err(first.clone().with_related_errors(rest.to_vec())),
+        }
+    }
+
+    /// Errors found by the last analysis
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    /// Warnings found by the last analysis
+    pub fn warnings(&self) -> &[ParseError] {
+        &self.warnings
+    }
+
+    /// Reset the analyzer state
+    pub fn reset(&mut self) {
+        self.symbols.clear();
+        self.errors.clear();
+        self.warnings.clear();
+        self.return_types.clear();
+        self.loop_depth = 0;
+    }
+
+    fn error(&mut self, kind: ParseErrorKind, position: Position, message: String) {
+        self.errors.push(ParseError::new(kind, position, message));
+    }
+
+    fn warn_unused(&mut self, symbols: Vec<SymbolInfo>) {
+        for symbol in symbols {
+            if matches!(symbol.symbol_type, SymbolType::Variable(_)) && !symbol.name.starts_with('_') {
+                self.warnings
+                    .push(ParseError::new(ParseErrorKind::UnusedVariable, symbol.position, format!("Unused variable '{}'", symbol.name)));
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &str, symbol_type: SymbolType, is_mutable: bool, position: Position) {
+        let info = SymbolInfo {
+            name: name.to_string(),
+            symbol_type,
+            position,
+            scope_level: 0,
+            is_mutable,
+        };
+        if let Err(error) = self.symbols.declare(info) {
+            self.errors.push(error);
+        }
+    }
+
+    fn declare_variable(&mut self, name: &str, base_type: BaseType, is_mutable: bool, position: Position) {
+        self.declare(name, SymbolType::Variable(variable_type(base_type)), is_mutable, position);
+    }
+
+    // Types
+
+    /// Combine the operand types of a binary operator, `None` if they do not match
+    fn unify(&self, left: &Type, right: &Type) -> Option<Type> {
+        let strict = self.config.strict_type_checking;
+        match (left, right) {
+            (Type::Unknown, _) | (_, Type::Unknown) => Some(Type::Unknown),
+            (Type::IntLiteral, Type::IntLiteral) => Some(Type::IntLiteral),
+            (Type::FloatLiteral, Type::FloatLiteral) => Some(Type::FloatLiteral),
+            (Type::IntLiteral, Type::FloatLiteral) | (Type::FloatLiteral, Type::IntLiteral) => (!strict).then_some(Type::FloatLiteral),
+            (Type::Known(base_type), literal @ (Type::IntLiteral | Type::FloatLiteral)) | (literal @ (Type::IntLiteral | Type::FloatLiteral), Type::Known(base_type)) => {
+                self.accepts(&Type::Known(base_type.clone()), literal).then(|| Type::Known(base_type.clone()))
+            }
+            (Type::Known(left), Type::Known(right)) if left == right => Some(Type::Known(left.clone())),
+            (Type::Known(left), Type::Known(right)) => match (numeric_rank(left), numeric_rank(right)) {
+                (Some(left_rank), Some(right_rank)) if !strict => Some(Type::Known(if right_rank > left_rank { right.clone() } else { left.clone() })),
+                _ => None,
+            },
+        }
+    }
+
+    /// Check if a value of type `found` can be stored where `expected` is required
+    fn accepts(&self, expected: &Type, found: &Type) -> bool {
+        let strict = self.config.strict_type_checking;
+        let Type::Known(expected) = expected else {
+            return self.unify(expected, found).is_some();
+        };
+
+        match found {
+            Type::Unknown => true,
+            Type::IntLiteral => is_integer(expected) || (!strict && is_float(expected)),
+            Type::FloatLiteral => is_float(expected) || (!strict && is_integer(expected)),
+            Type::Known(found) => found == expected || (!strict && numeric_rank(found).is_some() && numeric_rank(expected).is_some()),
+        }
+    }
+
+    fn expect_type(&mut self, expected: &Type, found: &Type, position: Position) {
+        if !self.accepts(expected, found) {
+            self.errors.push(ParseError::type_mismatch(position, expected.name(), found.name()));
+        }
+    }
+
+    // Statements
+
+    /// Check the statements of a block in the current scope
+    ///
+    /// Returns whether the block always leaves through `return`, `break` or `continue`.
+    fn check_block(&mut self, statements: &[AstNode]) -> bool {
+        self.declare_functions(statements);
+
+        let mut diverges = false;
+        let mut reported = false;
+        for statement in statements {
+            let is_function = matches!(statement.kind, NodeKind::Function { .. });
+            if diverges && !reported && !is_function {
+                self.warnings
+                    .push(ParseError::new(ParseErrorKind::UnreachableCode, statement.span.start, "Unreachable code".to_string()));
+                reported = true;
+            }
+            diverges |= self.check_statement(statement);
+        }
+
+        diverges
+    }
+
+    /// Check a block in a scope of its own
+    fn check_scoped_block(&mut self, statements: &[AstNode]) -> bool {
+        self.symbols.enter_scope();
+        let diverges = self.check_block(statements);
+        let unused = self.symbols.close_scope();
+        self.warn_unused(unused);
+        diverges
+    }
+
+    /// Declare the functions of a block up front so they can be called before their definition
+    fn declare_functions(&mut self, statements: &[AstNode]) {
+        for statement in statements {
+            if let NodeKind::Function { name, params, return_type, .. } = &statement.kind {
+                let function_type = FunctionType {
+                    parameters: params.iter().map(|param| variable_type(param.param_type.clone())).collect(),
+                    return_type: return_type.clone().filter(|return_type| *return_type != BaseType::Void).map(variable_type),
+                    is_external: false,
+                };
+                self.declare(name, SymbolType::Function(function_type), false, statement.span.start);
+            }
+        }
+    }
+
+    /// Check a statement, returning whether it always diverges
+    fn check_statement(&mut self, node: &AstNode) -> bool {
+        match &node.kind {
+            NodeKind::Let {
+                name,
+                mutable,
+                type_annotation,
+                value,
+            } => {
+                let found = value.as_ref().map(|value| (self.check_expression(value), value.span.start));
+                let base_type = match (type_annotation, found) {
+                    (Some(annotation), Some((found, position))) => {
+                        self.expect_type(&Type::from_base(annotation), &found, position);
+                        annotation.clone()
+                    }
+                    (Some(annotation), None) => annotation.clone(),
+                    (None, Some((found, _))) => found.to_base(),
+                    (None, None) => Type::Unknown.to_base(),
+                };
+                self.declare_variable(name, base_type, *mutable, node.span.start);
+                false
+            }
+            NodeKind::Function { name, params, return_type, body } => {
+                let return_type = return_type.clone().unwrap_or(BaseType::Void);
+                let returns_value = return_type != BaseType::Void;
+                let outer_loop_depth = std::mem::take(&mut self.loop_depth);
+                self.return_types.push(return_type.clone());
+
+                self.symbols.enter_scope();
+                for param in params {
+                    self.declare_variable(&param.name, param.param_type.clone(), false, param.span.start);
+                }
+                let diverges = self.check_scoped_block(body);
+                let unused = self.symbols.close_scope();
+                self.warn_unused(unused);
+
+                self.return_types.pop();
+                self.loop_depth = outer_loop_depth;
+
+                if returns_value && !diverges {
+                    self.error(
+                        ParseErrorKind::TypeMismatch,
+                        node.span.start,
+                        format!("Function '{}' must return a value of type '{}' on every path", name, type_name(&return_type)),
+                    );
+                }
+                false
+            }
+            NodeKind::If { condition, then_branch, else_branch } => {
+                self.check_condition(condition);
+                let then_diverges = self.check_scoped_block(then_branch);
+                let else_diverges = else_branch.as_ref().is_some_and(|else_branch| self.check_scoped_block(else_branch));
+                then_diverges && else_diverges
+            }
+            NodeKind::While { condition, body } => {
+                self.check_condition(condition);
+                self.loop_depth += 1;
+                self.check_scoped_block(body);
+                self.loop_depth -= 1;
+                false
+            }
+            NodeKind::Return(value) => {
+                let found = match value {
+                    Some(value) => (self.check_expression(value), value.span.start),
+                    None => (Type::Known(BaseType::Void), node.span.start),
+                };
+                // Top-level returns end the program and may return anything
+                if let Some(expected) = self.return_types.last() {
+                    let expected = Type::Known(expected.clone());
+                    self.expect_type(&expected, &found.0, found.1);
+                }
+                true
+            }
+            NodeKind::Break | NodeKind::Continue => {
+                if self.loop_depth == 0 {
+                    let keyword = if matches!(node.kind, NodeKind::Break) { "break" } else { "continue" };
+                    self.error(ParseErrorKind::InvalidOperation, node.span.start, format!("'{}' outside of a loop", keyword));
+                }
+                true
+            }
+            NodeKind::Block(statements) => self.check_scoped_block(statements),
+            NodeKind::Expression(expression) => {
+                self.check_expression(expression);
+                false
+            }
+            _ => {
+                self.check_expression(node);
+                false
+            }
+        }
+    }
+
+    fn check_condition(&mut self, condition: &AstNode) {
+        let found = self.check_expression(condition);
+        self.expect_type(&Type::Known(BaseType::Bool), &found, condition.span.start);
+    }
+
+    // Expressions
+
+    fn check_expression(&mut self, node: &AstNode) -> Type {
+        match &node.kind {
+            NodeKind::Literal(literal) => match literal {
+                Literal::Integer(_) => Type::IntLiteral,
+                Literal::Float(_) => Type::FloatLiteral,
+                Literal::String(_) => Type::Known(BaseType::String),
+                Literal::Boolean(_) => Type::Known(BaseType::Bool),
+                Literal::Char(_) => Type::Known(BaseType::Custom("char".to_string())),
+                Literal::Null => Type::Unknown,
+            },
+            NodeKind::Identifier(name) => match self.symbols.resolve(name).map(|info| info.symbol_type.clone()) {
+                Some(SymbolType::Variable(variable)) => Type::from_base(&variable.base_type),
+                Some(SymbolType::Constant(constant)) => Type::from_base(&constant.base_type),
+                Some(_) => Type::Unknown,
+                None => {
+                    self.errors.push(ParseError::undefined_symbol(node.span.start, name.clone()));
+                    Type::Unknown
+                }
+            },
+            NodeKind::Unary { op, operand } => {
+                let found = self.check_expression(operand);
+                let valid = match op {
+                    Operator::Minus => found.is_numeric(),
+                    Operator::Not => found.is(BaseType::Bool),
+                    _ => found.is_integer(),
+                };
+                if !valid {
+                    self.error(
+                        ParseErrorKind::InvalidOperation,
+                        operand.span.start,
+                        format!("Operator '{}' cannot be applied to '{}'", op, found.name()),
+                    );
+                    return Type::Unknown;
+                }
+                if *op == Operator::Not { Type::Known(BaseType::Bool) } else { found }
+            }
+            NodeKind::Binary { op, left, right } => self.check_binary(op, left, right),
+            NodeKind::Assign { op, target, value } => {
+                self.check_assignment(op, target, value);
+                Type::Known(BaseType::Void)
+            }
+            NodeKind::Call { callee, args } => self.check_call(callee, args),
+            NodeKind::FieldAccess { object, .. } => {
+                self.check_expression(object);
+                Type::Unknown
+            }
+            NodeKind::Index { object, index } => {
+                self.check_expression(object);
+                let found = self.check_expression(index);
+                if !found.is_integer() {
+                    self.errors.push(ParseError::type_mismatch(index.span.start, "integer".to_string(), found.name()));
+                }
+                Type::Unknown
+            }
+            NodeKind::StateAccess { operation, args } => {
+                for arg in args {
+                    self.check_expression(arg);
+                }
+                match operation {
+                    StateOperation::Load => Type::Unknown,
+                    StateOperation::Store | StateOperation::Clear => Type::Known(BaseType::Void),
+                    StateOperation::Exists => Type::Known(BaseType::Bool),
+                    StateOperation::Size => Type::Known(BaseType::U64),
+                }
+            }
+            _ => {
+                self.error(ParseErrorKind::InternalError, node.span.start, "Statement used as an expression".to_string());
+                Type::Unknown
+            }
+        }
+    }
+
+    /// Check that an operand fits the operator, reporting it otherwise
+    fn check_operand(&mut self, op: &Operator, operand: &AstNode, found: &Type, valid: bool) -> bool {
+        if !valid {
+            self.error(
+                ParseErrorKind::InvalidOperation,
+                operand.span.start,
+                format!("Operator '{}' cannot be applied to '{}'", op, found.name()),
+            );
+        }
+        valid
+    }
+
+    fn check_binary(&mut self, op: &Operator, left: &AstNode, right: &AstNode) -> Type {
+        let left_type = self.check_expression(left);
+        let right_type = self.check_expression(right);
+
+        let is_string = |found: &Type| matches!(found, Type::Known(BaseType::String));
+        let operands_valid = |found: &Type| match op {
+            Operator::And | Operator::Or => found.is(BaseType::Bool),
+            Operator::Equal | Operator::NotEqual => true,
+            Operator::Plus => found.is_numeric() || is_string(found),
+            Operator::BitAnd | Operator::BitOr | Operator::BitXor | Operator::LeftShift | Operator::RightShift => found.is_integer(),
+            _ => found.is_numeric(),
+        };
+
+        let left_valid = operands_valid(&left_type);
+        let right_valid = operands_valid(&right_type);
+        let left_valid = self.check_operand(op, left, &left_type, left_valid);
+        let right_valid = self.check_operand(op, right, &right_type, right_valid);
+        if !left_valid || !right_valid {
+            return Type::Unknown;
+        }
+
+        // Shifts take their type from the shifted value alone
+        let result = if matches!(op, Operator::LeftShift | Operator::RightShift) {
+            left_type
+        } else {
+            match self.unify(&left_type, &right_type) {
+                Some(result) => result,
+                None => {
+                    self.errors.push(ParseError::type_mismatch(right.span.start, left_type.name(), right_type.name()));
+                    return Type::Unknown;
+                }
+            }
+        };
+
+        match op {
+            Operator::And | Operator::Or | Operator::Equal | Operator::NotEqual | Operator::Less | Operator::LessEqual | Operator::Greater | Operator::GreaterEqual => Type::Known(BaseType::Bool),
+            _ => result,
+        }
+    }
+
+    fn check_assignment(&mut self, op: &Operator, target: &AstNode, value: &AstNode) {
+        let target_type = match &target.kind {
+            // Assigning does not count as using the variable
+            NodeKind::Identifier(name) => match self.symbols.lookup(name).cloned() {
+                Some(SymbolInfo {
+                    symbol_type: SymbolType::Variable(variable),
+                    is_mutable,
+                    position,
+                    ..
+                }) => {
+                    if !is_mutable {
+                        self.error(
+                            ParseErrorKind::InvalidAssignment,
+                            target.span.start,
+                            format!("Cannot assign to immutable variable '{}' declared at {}, declare it with 'let mut'", name, position),
+                        );
+                    }
+                    Type::from_base(&variable.base_type)
+                }
+                Some(_) => {
+                    self.error(ParseErrorKind::InvalidAssignment, target.span.start, format!("Cannot assign to '{}', it is not a variable", name));
+                    Type::Unknown
+                }
+                None => {
+                    self.errors.push(ParseError::undefined_symbol(target.span.start, name.clone()));
+                    Type::Unknown
+                }
+            },
+            _ => self.check_expression(target),
+        };
+        let value_type = self.check_expression(value);
+
+        let valid = match op {
+            Operator::Assign => true,
+            Operator::PlusAssign => target_type.is_numeric() || target_type == Type::Known(BaseType::String),
+            _ => target_type.is_numeric(),
+        };
+        if self.check_operand(op, target, &target_type, valid) {
+            self.expect_type(&target_type, &value_type, value.span.start);
+        }
+    }
+
+    fn check_call(&mut self, callee: &AstNode, args: &[AstNode]) -> Type {
+        let arg_types: Vec<(Type, Position)> = args.iter().map(|arg| (self.check_expression(arg), arg.span.start)).collect();
+
+        let NodeKind::Identifier(name) = &callee.kind else {
+            self.check_expression(callee);
+            return Type::Unknown;
+        };
+
+        let function = match self.symbols.resolve(name).map(|info| info.symbol_type.clone()) {
+            Some(SymbolType::Function(function)) => function,
+            Some(_) => {
+                self.error(ParseErrorKind::InvalidOperation, callee.span.start, format!("'{}' is not a function", name));
+                return Type::Unknown;
+            }
+            None => {
+                self.errors.push(ParseError::undefined_symbol(callee.span.start, name.clone()));
+                return Type::Unknown;
+            }
+        };
+
+        if function.parameters.len() != args.len() {
+            self.error(
+                ParseErrorKind::ArgumentCountMismatch,
+                callee.span.start,
+                format!("Function '{}' takes {} argument(s), found {}", name, function.parameters.len(), args.len()),
+            );
+        } else {
+            for (param, (found, position)) in function.parameters.iter().zip(&arg_types) {
+                self.expect_type(&Type::from_base(&param.base_type), found, *position);
+            }
+        }
+
+        function.return_type.map_or(Type::Known(BaseType::Void), |return_type| Type::from_base(&return_type.base_type))
+    }
+}
+
+impl SemanticAnalyzer<DotVMAst> for DotVMSemanticAnalyzer {
+    fn analyze(&mut self, ast: &DotVMAst) -> ParseResult<()> {
+        DotVMSemanticAnalyzer::analyze(self, ast)
+    }
+
+    fn check_semantics(&self, ast: &DotVMAst) -> Vec<ParseError> {
+        let mut analyzer = Self::with_config(self.config.clone());
+        let _ = analyzer.analyze(ast);
+        analyzer.errors
+    }
+
+    fn get_symbols(&self) -> &dyn SymbolTable {
+        &self.symbols
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::dotvm::{DotVMLexer, DotVMSyntaxParser};
+    use crate::parser::traits::SyntaxParser;
+
+    fn analyze_with(source: &str, config: ParserConfig) -> (Vec<ParseError>, Vec<ParseError>) {
+        let tokens = DotVMLexer::new().tokenize(source).unwrap();
+        let ast = DotVMSyntaxParser::new().parse_tokens(&tokens).unwrap();
+        let mut analyzer = DotVMSemanticAnalyzer::with_config(config);
+        let result = analyzer.analyze(&ast);
+        assert_eq!(result.is_ok(), analyzer.errors().is_empty());
+        (analyzer.errors().to_vec(), analyzer.warnings().to_vec())
+    }
+
+    fn errors(source: &str) -> Vec<(ParseErrorKind, Position)> {
+        analyze_with(source, ParserConfig::default()).0.into_iter().map(|error| (error.kind, error.position)).collect()
+    }
+
+    fn warnings(source: &str) -> Vec<(ParseErrorKind, Position)> {
+        let (errors, warnings) = analyze_with(source, ParserConfig::default());
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+        warnings.into_iter().map(|warning| (warning.kind, warning.position)).collect()
+    }
+
+    #[test]
+    fn test_valid_program() {
+        let source = "\
+fn add(a: i64, b: i64) -> i64 {
+    return a + b;
+}
+let mut total: i64 = add(1, 2);
+total += 3;
+if total > 5 && state.exists(\"key\") {
+    state.store(\"key\", total);
+}
+";
+        assert!(errors(source).is_empty());
+        assert!(warnings(source).is_empty());
+    }
+
+    #[test]
+    fn test_symbol_resolution() {
+        // Use before definition
+        assert_eq!(errors("let y = x;\nlet x = 1;"), vec![(ParseErrorKind::UndefinedSymbol, Position::new(1, 9))]);
+        // Functions may be called before they are defined
+        assert!(errors("let x = one();\nlet y = x;\nfn one() -> i32 { return 1; }").is_empty());
+        // Inner scopes end with their block
+        assert_eq!(errors("{ let inner = 1; }\nlet x = inner;"), vec![(ParseErrorKind::UndefinedSymbol, Position::new(2, 9))]);
+    }
+
+    #[test]
+    fn test_duplicate_declarations() {
+        let (found, _) = analyze_with("let x = 1;\nlet x = 2;", ParserConfig::default());
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].kind, ParseErrorKind::DuplicateDeclaration);
+        assert_eq!(found[0].position, Position::new(2, 1));
+        assert_eq!(found[0].related[0].position, Position::new(1, 1));
+
+        assert_eq!(errors("fn f() {}\nfn f() {}")[0].0, ParseErrorKind::DuplicateDeclaration);
+        assert_eq!(errors("fn f(a: i32, a: i32) {}")[0].0, ParseErrorKind::DuplicateDeclaration);
+        // Shadowing in an inner scope is allowed
+        assert!(errors("let x = 1;\n{ let x = x; let y = x; }").is_empty());
+    }
+
+    #[test]
+    fn test_type_checking() {
+        assert_eq!(errors("let x: i32 = true;"), vec![(ParseErrorKind::TypeMismatch, Position::new(1, 14))]);
+        assert_eq!(errors("let x = 1 + \"one\";"), vec![(ParseErrorKind::TypeMismatch, Position::new(1, 13))]);
+        assert_eq!(errors("let x = !1;"), vec![(ParseErrorKind::InvalidOperation, Position::new(1, 10))]);
+        assert_eq!(errors("if 1 { }"), vec![(ParseErrorKind::TypeMismatch, Position::new(1, 4))]);
+        assert_eq!(errors("let s = \"a\" + \"b\";\nlet n: u8 = 7;\nlet b: bool = n < 8;\nlet i = s + s;").len(), 0);
+        // Results of state loads are not known and fit anywhere
+        assert!(errors("let n: i32 = state.load(\"n\");").is_empty());
+    }
+
+    #[test]
+    fn test_strict_type_checking() {
+        let source = "let a: i32 = 1;\nlet b: i64 = a;\nlet c: f64 = b * 2;";
+        let strict = errors(source);
+        assert_eq!(strict, vec![(ParseErrorKind::TypeMismatch, Position::new(2, 14)), (ParseErrorKind::TypeMismatch, Position::new(3, 14))]);
+
+        let (lenient, _) = analyze_with(source, ParserConfig::new().with_strict_types(false));
+        assert!(lenient.is_empty());
+
+        // Non-numeric types never convert
+        let (lenient, _) = analyze_with("let a: i32 = \"one\";", ParserConfig::new().with_strict_types(false));
+        assert_eq!(lenient[0].kind, ParseErrorKind::TypeMismatch);
+    }
+
+    #[test]
+    fn test_function_calls() {
+        let functions = "fn scale(value: i64, factor: i64) -> i64 { return value * factor; }\n";
+        assert_eq!(errors(&format!("{}let x = scale(1);", functions)), vec![(ParseErrorKind::ArgumentCountMismatch, Position::new(2, 9))]);
+        assert_eq!(errors(&format!("{}let x = scale(1, true);", functions)), vec![(ParseErrorKind::TypeMismatch, Position::new(2, 18))]);
+        assert_eq!(errors(&format!("{}let x: bool = scale(1, 2);", functions)), vec![(ParseErrorKind::TypeMismatch, Position::new(2, 15))]);
+        assert_eq!(errors("let f = 1;\nf();"), vec![(ParseErrorKind::InvalidOperation, Position::new(2, 1))]);
+        assert_eq!(errors("missing();"), vec![(ParseErrorKind::UndefinedSymbol, Position::new(1, 1))]);
+    }
+
+    #[test]
+    fn test_returns() {
+        assert_eq!(errors("fn f() -> i32 { return true; }"), vec![(ParseErrorKind::TypeMismatch, Position::new(1, 24))]);
+        assert_eq!(errors("fn f() { return 1; }"), vec![(ParseErrorKind::TypeMismatch, Position::new(1, 17))]);
+        assert_eq!(errors("fn f(x: i32) -> i32 { if x > 0 { return 1; } }"), vec![(ParseErrorKind::TypeMismatch, Position::new(1, 1))]);
+        assert!(errors("fn f(x: i32) -> i32 { if x > 0 { return 1; } else { return 0; } }").is_empty());
+    }
+
+    #[test]
+    fn test_immutable_assignment() {
+        let (found, _) = analyze_with("let x = 1;\nx = 2;", ParserConfig::default());
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].kind, ParseErrorKind::InvalidAssignment);
+        assert_eq!(found[0].position, Position::new(2, 1));
+        assert!(found[0].message.contains("let mut"));
+
+        assert!(errors("let mut x = 1;\nx = 2;\nlet y = x;").is_empty());
+        assert_eq!(errors("fn f(a: i32) { a += 1; }")[0].0, ParseErrorKind::InvalidAssignment);
+        assert_eq!(errors("let mut s = \"a\";\ns = 1;"), vec![(ParseErrorKind::TypeMismatch, Position::new(2, 5))]);
+    }
+
+    #[test]
+    fn test_loop_control() {
+        assert_eq!(errors("break;"), vec![(ParseErrorKind::InvalidOperation, Position::new(1, 1))]);
+        assert!(errors("while true { break; }").is_empty());
+        // Loops do not extend into nested functions
+        assert_eq!(errors("while true { fn f() { continue; } }")[0].0, ParseErrorKind::InvalidOperation);
+    }
+
+    #[test]
+    fn test_all_errors_are_reported() {
+        let tokens = DotVMLexer::new().tokenize("let a = b;\nlet c: bool = 1;\nbreak;").unwrap();
+        let ast = DotVMSyntaxParser::new().parse_tokens(&tokens).unwrap();
+
+        let error = DotVMSemanticAnalyzer::new().analyze(&ast).unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UndefinedSymbol);
+        let related: Vec<ParseErrorKind> = error.related.iter().map(|error| error.kind.clone()).collect();
+        assert_eq!(related, vec![ParseErrorKind::TypeMismatch, ParseErrorKind::InvalidOperation]);
+    }
+
+    #[test]
+    fn test_warnings() {
+        assert_eq!(warnings("let x = 1;"), vec![(ParseErrorKind::UnusedVariable, Position::new(1, 1))]);
+        assert_eq!(warnings("fn f(value: i32) {}"), vec![(ParseErrorKind::UnusedVariable, Position::new(1, 6))]);
+        assert!(warnings("let _ignored = 1;").is_empty());
+        // Assigning is not using
+        assert_eq!(warnings("let mut x = 1;\nx = 2;"), vec![(ParseErrorKind::UnusedVariable, Position::new(1, 1))]);
+
+        let source = "fn f() -> i32 {\n    return 1;\n    let y = 2;\n    return y;\n}";
+        assert_eq!(warnings(source), vec![(ParseErrorKind::UnreachableCode, Position::new(3, 5))]);
+
+        let (_, warnings) = analyze_with("while true { break; state.clear(\"k\"); }", ParserConfig::default());
+        assert!(warnings.iter().all(ParseError::is_warning));
+        assert_eq!(warnings[0].kind, ParseErrorKind::UnreachableCode);
+    }
+
+    #[test]
+    fn test_symbol_table_scopes() {
+        let mut table = ScopedSymbolTable::new();
+        let int = SymbolType::Variable(variable_type(BaseType::I32));
+        table.define("x".to_string(), int.clone()).unwrap();
+        assert!(table.define("x".to_string(), int.clone()).is_err());
+
+        table.enter_scope();
+        assert_eq!(table.scope_level(), 1);
+        table.define("x".to_string(), int.clone()).unwrap();
+        assert_eq!(table.lookup("x").unwrap().scope_level, 1);
+        assert!(table.resolve("x").is_some());
+        assert!(table.close_scope().is_empty());
+
+        assert_eq!(table.lookup("x").unwrap().scope_level, 0);
+        assert_eq!(table.close_scope().len(), 1);
+        assert_eq!(table.scope_level(), 0);
+    }
+}
@@ -0,0 +1,1013 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! DotVM syntax parser producing the AST
+//!
+//! The grammar, with statements terminated by `;` unless they end in a block:
+//!
+//! ```text
+//! program    := statement* EOF
+//! statement  := function | let | if | while | return | break | continue | block | expression ";"
+//! function   := "fn" IDENT "(" (param ("," param)*)? ")" ("->" type)? block
+//! param      := IDENT ":" type
+//! let        := "let" "mut"? IDENT (":" type)? ("=" expression)? ";"
+//! if         := "if" expression block ("else" (if | block))?
+//! while      := "while" expression block
+//! block      := "{" statement* "}"
+//! expression := binary expressions by `Operator::precedence`, then unary, call,
+//!               field access, indexing and primary expressions
+//! ```
+//!
+//! Calls on the `state` receiver, such as `state.load(key)`, are parsed as
+//! [`NodeKind::StateAccess`] instead of ordinary method calls.
+
+use crate::parser::common::{Delimiter, Keyword, Operator, ParseError, ParseErrorKind, ParseResult, ParserConfig, Position, Token, TokenType, position::Span};
+use crate::parser::traits::{BaseType, SyntaxParser};
+
+/// Receiver name of state access calls
+pub const STATE_RECEIVER: &str = "state";
+
+/// A parsed DotVM program
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DotVMAst {
+    /// Top-level statements and function definitions in source order
+    pub items: Vec<AstNode>,
+}
+
+impl DotVMAst {
+    /// Create a program from its top-level items
+    pub fn new(items: Vec<AstNode>) -> Self {
+        Self { items }
+    }
+
+    /// Check if the program has no items
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Top-level function definitions
+    pub fn functions(&self) -> impl Iterator<Item = &AstNode> {
+        self.items.iter().filter(|item| matches!(item.kind, NodeKind::Function { .. }))
+    }
+}
+
+/// A node of the syntax tree together with the source it was parsed from
+#[derive(Debug, Clone, PartialEq)]
+pub struct AstNode {
+    /// What the node is
+    pub kind: NodeKind,
+    /// Source span covering the whole construct
+    pub span: Span,
+}
+
+impl AstNode {
+    /// Create a new node
+    pub fn new(kind: NodeKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    /// Check if this node can be the target of an assignment
+    pub fn is_assignable(&self) -> bool {
+        matches!(self.kind, NodeKind::Identifier(_) | NodeKind::FieldAccess { .. } | NodeKind::Index { .. })
+    }
+}
+
+/// Statements and expressions of the DotVM language
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeKind {
+    // Statements
+    Let {
+        name: String,
+        mutable: bool,
+        type_annotation: Option<BaseType>,
+        value: Option<Box<AstNode>>,
+    },
+    Function {
+        name: String,
+        params: Vec<Parameter>,
+        return_type: Option<BaseType>,
+        body: Vec<AstNode>,
+    },
+    If {
+        condition: Box<AstNode>,
+        then_branch: Vec<AstNode>,
+        /// `else if` chains hold a single nested `If`
+        else_branch: Option<Vec<AstNode>>,
+    },
+    While {
+        condition: Box<AstNode>,
+        body: Vec<AstNode>,
+    },
+    Return(Option<Box<AstNode>>),
+    Break,
+    Continue,
+    Block(Vec<AstNode>),
+    /// Expression evaluated for its side effects
+    Expression(Box<AstNode>),
+
+    // Expressions
+    Literal(Literal),
+    Identifier(String),
+    Unary {
+        op: Operator,
+        operand: Box<AstNode>,
+    },
+    Binary {
+        op: Operator,
+        left: Box<AstNode>,
+        right: Box<AstNode>,
+    },
+    /// Plain or compound assignment, `op` is the assignment operator
+    Assign {
+        op: Operator,
+        target: Box<AstNode>,
+        value: Box<AstNode>,
+    },
+    Call {
+        callee: Box<AstNode>,
+        args: Vec<AstNode>,
+    },
+    FieldAccess {
+        object: Box<AstNode>,
+        field: String,
+    },
+    Index {
+        object: Box<AstNode>,
+        index: Box<AstNode>,
+    },
+    StateAccess {
+        operation: StateOperation,
+        args: Vec<AstNode>,
+    },
+}
+
+/// Literal values
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Boolean(bool),
+    Char(char),
+    Null,
+}
+
+/// Function parameter
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    pub param_type: BaseType,
+    pub span: Span,
+}
+
+/// Operations on the dot's persistent state, written `state.<operation>(...)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StateOperation {
+    /// `state.load(key)`
+    Load,
+    /// `state.store(key, value)`
+    Store,
+    /// `state.exists(key)`
+    Exists,
+    /// `state.clear(key)`
+    Clear,
+    /// `state.size(key)`
+    Size,
+}
+
+impl StateOperation {
+    /// All state operations
+    pub const ALL: [StateOperation; 5] = [StateOperation::Load, StateOperation::Store, StateOperation::Exists, StateOperation::Clear, StateOperation::Size];
+
+    /// Get the operation from its method name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|operation| operation.name() == name)
+    }
+
+    /// Method name of the operation
+    pub fn name(&self) -> &'static str {
+        match self {
+            StateOperation::Load => "load",
+            StateOperation::Store => "store",
+            StateOperation::Exists => "exists",
+            StateOperation::Clear => "clear",
+            StateOperation::Size => "size",
+        }
+    }
+
+    /// Number of arguments the operation takes
+    pub fn arity(&self) -> usize {
+        match self {
+            StateOperation::Store => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// DotVM syntax parser
+pub struct DotVMSyntaxParser {
+    /// Tokens being parsed, without comments, always ending in EOF
+    tokens: Vec<Token>,
+    /// Index of the current token
+    current: usize,
+    /// Parser configuration
+    config: ParserConfig,
+    /// Current nesting depth of statements and expressions
+    depth: usize,
+}
+
+impl DotVMSyntaxParser {
+    /// Create a new syntax parser with the default configuration
+    pub fn new() -> Self {
+        Self::with_config(ParserConfig::default())
+    }
+
+    /// Create a syntax parser with a custom configuration
+    pub fn with_config(config: ParserConfig) -> Self {
+        Self {
+            tokens: vec![Token::at_position(TokenType::Eof, String::new(), Position::start())],
+            current: 0,
+            config,
+            depth: 0,
+        }
+    }
+
+    /// Get the parser configuration
+    pub fn config(&self) -> &ParserConfig {
+        &self.config
+    }
+
+    /// Replace the parser configuration
+    pub fn set_config(&mut self, config: ParserConfig) {
+        self.config = config;
+    }
+
+    /// Reset the parser to an empty token stream
+    pub fn reset(&mut self) {
+        self.load(&[]);
+    }
+
+    /// Load tokens to parse, dropping comments and whitespace
+    fn load(&mut self, tokens: &[Token]) {
+        self.tokens = tokens
+            .iter()
+            .filter(|token| !matches!(token.token_type, TokenType::Comment(_) | TokenType::Whitespace))
+            .cloned()
+            .collect();
+
+        if !matches!(self.tokens.last().map(|token| &token.token_type), Some(TokenType::Eof)) {
+            let end = self.tokens.last().map_or(Position::start(), |token| token.span.end);
+            self.tokens.push(Token::at_position(TokenType::Eof, String::new(), end));
+        }
+
+        self.current = 0;
+        self.depth = 0;
+    }
+
+    // Token navigation
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.current]
+    }
+
+    fn previous(&self) -> Option<&Token> {
+        self.current.checked_sub(1).map(|index| &self.tokens[index])
+    }
+
+    fn is_at_end(&self) -> bool {
+        matches!(self.peek().token_type, TokenType::Eof)
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.peek().clone();
+        if !self.is_at_end() {
+            self.current += 1;
+        }
+        token
+    }
+
+    fn check_delimiter(&self, delimiter: Delimiter) -> bool {
+        self.peek().token_type == TokenType::Delimiter(delimiter)
+    }
+
+    fn check_keyword(&self, keyword: Keyword) -> bool {
+        self.peek().token_type == TokenType::Keyword(keyword)
+    }
+
+    fn check_operator(&self, operator: Operator) -> bool {
+        self.peek().token_type == TokenType::Operator(operator)
+    }
+
+    fn match_delimiter(&mut self, delimiter: Delimiter) -> bool {
+        let matched = self.check_delimiter(delimiter);
+        if matched {
+            self.advance();
+        }
+        matched
+    }
+
+    fn match_keyword(&mut self, keyword: Keyword) -> bool {
+        let matched = self.check_keyword(keyword);
+        if matched {
+            self.advance();
+        }
+        matched
+    }
+
+    fn match_operator(&mut self, operator: Operator) -> bool {
+        let matched = self.check_operator(operator);
+        if matched {
+            self.advance();
+        }
+        matched
+    }
+
+    /// Consume a delimiter or fail with `what` describing it
+    fn expect_delimiter(&mut self, delimiter: Delimiter, what: &str) -> ParseResult<Token> {
+        if self.check_delimiter(delimiter) { Ok(self.advance()) } else { Err(self.error_expected(what)) }
+    }
+
+    /// Consume an identifier and return its name
+    fn expect_identifier(&mut self, what: &str) -> ParseResult<String> {
+        match &self.peek().token_type {
+            TokenType::Identifier(name) => {
+                let name = name.clone();
+                self.advance();
+                Ok(name)
+            }
+            _ => Err(self.error_expected(what)),
+        }
+    }
+
+    /// Error for a missing construct at the current token
+    ///
+    /// When the current token is on a later line than the previous one, as with
+    /// a missing `;`, the error points just past the previous token instead.
+    fn error_expected(&self, what: &str) -> ParseError {
+        let token = self.peek();
+        let position = match self.previous() {
+            Some(previous) if matches!(token.token_type, TokenType::Eof) || previous.span.end.line < token.span.start.line => previous.span.end,
+            _ => token.span.start,
+        };
+
+        match token.token_type {
+            TokenType::Eof => ParseError::new(ParseErrorKind::UnexpectedEof, position, format!("Expected {}, found end of file", what)),
+            _ => ParseError::new(ParseErrorKind::ExpectedToken, position, format!("Expected {}, found '{}'", what, token.lexeme)),
+        }
+    }
+
+    /// Span from `start` to the end of the last consumed token
+    fn span_from(&self, start: Span) -> Span {
+        self.previous().map_or(start, |previous| start.merge(previous.span))
+    }
+
+    /// Go one nesting level deeper, failing past the configured limit
+    ///
+    /// Callers decrement `depth` again once the nested construct is parsed.
+    fn enter(&mut self) -> ParseResult<()> {
+        if self.depth >= self.config.max_recursion_depth {
+            return Err(ParseError::new(
+                ParseErrorKind::RecursionLimitExceeded,
+                self.peek().span.start,
+                format!("Maximum nesting depth of {} exceeded", self.config.max_recursion_depth),
+            ));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    // Statements
+
+    fn parse_statement(&mut self) -> ParseResult<AstNode> {
+        self.enter()?;
+        let statement = self.parse_statement_kind();
+        self.depth -= 1;
+        statement
+    }
+
+    fn parse_statement_kind(&mut self) -> ParseResult<AstNode> {
+        match &self.peek().token_type {
+            TokenType::Keyword(Keyword::Fn) => self.parse_function(),
+            TokenType::Keyword(Keyword::Let) => self.parse_let(),
+            TokenType::Keyword(Keyword::If) => self.parse_if(),
+            TokenType::Keyword(Keyword::While) => self.parse_while(),
+            TokenType::Keyword(Keyword::Return) => self.parse_return(),
+            TokenType::Keyword(Keyword::Break) => self.parse_jump(NodeKind::Break, "'break'"),
+            TokenType::Keyword(Keyword::Continue) => self.parse_jump(NodeKind::Continue, "'continue'"),
+            TokenType::Delimiter(Delimiter::LeftBrace) => {
+                let start = self.peek().span;
+                let statements = self.parse_block()?;
+                Ok(AstNode::new(NodeKind::Block(statements), self.span_from(start)))
+            }
+            _ => {
+                let start = self.peek().span;
+                let expression = self.parse_expression()?;
+                self.expect_delimiter(Delimiter::Semicolon, "';' after expression")?;
+                Ok(AstNode::new(NodeKind::Expression(Box::new(expression)), self.span_from(start)))
+            }
+        }
+    }
+
+    fn parse_function(&mut self) -> ParseResult<AstNode> {
+        let start = self.advance().span;
+        let name = self.expect_identifier("function name after 'fn'")?;
+
+        self.expect_delimiter(Delimiter::LeftParen, "'(' after function name")?;
+        let mut params = Vec::new();
+        if !self.check_delimiter(Delimiter::RightParen) {
+            loop {
+                let param_start = self.peek().span;
+                let param_name = self.expect_identifier("parameter name")?;
+                self.expect_delimiter(Delimiter::Colon, "':' after parameter name")?;
+                let param_type = self.parse_type()?;
+                params.push(Parameter {
+                    name: param_name,
+                    param_type,
+                    span: self.span_from(param_start),
+                });
+
+                if !self.match_delimiter(Delimiter::Comma) {
+                    break;
+                }
+            }
+        }
+        self.expect_delimiter(Delimiter::RightParen, "')' after parameters")?;
+
+        let return_type = if self.match_operator(Operator::Arrow) { Some(self.parse_type()?) } else { None };
+        let body = self.parse_block()?;
+
+        Ok(AstNode::new(NodeKind::Function { name, params, return_type, body }, self.span_from(start)))
+    }
+
+    fn parse_let(&mut self) -> ParseResult<AstNode> {
+        let start = self.advance().span;
+        let mutable = self.match_keyword(Keyword::Mut);
+        let name = self.expect_identifier("variable name after 'let'")?;
+
+        let type_annotation = if self.match_delimiter(Delimiter::Colon) { Some(self.parse_type()?) } else { None };
+        let value = if self.match_operator(Operator::Assign) { Some(Box::new(self.parse_expression()?)) } else { None };
+        self.expect_delimiter(Delimiter::Semicolon, "';' after let binding")?;
+
+        Ok(AstNode::new(
+            NodeKind::Let {
+                name,
+                mutable,
+                type_annotation,
+                value,
+            },
+            self.span_from(start),
+        ))
+    }
+
+    fn parse_if(&mut self) -> ParseResult<AstNode> {
+        let start = self.advance().span;
+        let condition = Box::new(self.parse_expression()?);
+        let then_branch = self.parse_block()?;
+
+        let else_branch = if self.match_keyword(Keyword::Else) {
+            if self.check_keyword(Keyword::If) {
+                self.enter()?;
+                let nested_if = self.parse_if();
+                self.depth -= 1;
+                Some(vec![nested_if?])
+            } else {
+                Some(self.parse_block()?)
+            }
+        } else {
+            None
+        };
+
+        Ok(AstNode::new(NodeKind::If { condition, then_branch, else_branch }, self.span_from(start)))
+    }
+
+    fn parse_while(&mut self) -> ParseResult<AstNode> {
+        let start = self.advance().span;
+        let condition = Box::new(self.parse_expression()?);
+        let body = self.parse_block()?;
+
+        Ok(AstNode::new(NodeKind::While { condition, body }, self.span_from(start)))
+    }
+
+    fn parse_return(&mut self) -> ParseResult<AstNode> {
+        let start = self.advance().span;
+        let value = if self.check_delimiter(Delimiter::Semicolon) {
+            None
+        } else {
+            Some(Box::new(self.parse_expression()?))
+        };
+        self.expect_delimiter(Delimiter::Semicolon, "';' after return")?;
+
+        Ok(AstNode::new(NodeKind::Return(value), self.span_from(start)))
+    }
+
+    fn parse_jump(&mut self, kind: NodeKind, keyword: &str) -> ParseResult<AstNode> {
+        let start = self.advance().span;
+        self.expect_delimiter(Delimiter::Semicolon, &format!("';' after {}", keyword))?;
+
+        Ok(AstNode::new(kind, self.span_from(start)))
+    }
+
+    /// Parse `{ statement* }`
+    fn parse_block(&mut self) -> ParseResult<Vec<AstNode>> {
+        self.expect_delimiter(Delimiter::LeftBrace, "'{'")?;
+
+        let mut statements = Vec::new();
+        while !self.check_delimiter(Delimiter::RightBrace) && !self.is_at_end() {
+            statements.push(self.parse_statement()?);
+        }
+        self.expect_delimiter(Delimiter::RightBrace, "'}' to close the block")?;
+
+        Ok(statements)
+    }
+
+    fn parse_type(&mut self) -> ParseResult<BaseType> {
+        let base_type = match &self.peek().token_type {
+            TokenType::Keyword(keyword) if keyword.is_type_keyword() => match keyword {
+                Keyword::I8 => BaseType::I8,
+                Keyword::I16 => BaseType::I16,
+                Keyword::I32 => BaseType::I32,
+                Keyword::I64 => BaseType::I64,
+                Keyword::U8 => BaseType::U8,
+                Keyword::U16 => BaseType::U16,
+                Keyword::U32 => BaseType::U32,
+                Keyword::U64 => BaseType::U64,
+                Keyword::F32 => BaseType::F32,
+                Keyword::F64 => BaseType::F64,
+                Keyword::Bool => BaseType::Bool,
+                Keyword::String => BaseType::String,
+                Keyword::Void => BaseType::Void,
+                other => BaseType::Custom(other.to_string()),
+            },
+            TokenType::Identifier(name) => BaseType::Custom(name.clone()),
+            _ => return Err(self.error_expected("type")),
+        };
+        self.advance();
+
+        Ok(base_type)
+    }
+
+    // Expressions
+
+    /// Parse a full expression, including assignments
+    pub fn parse_expression(&mut self) -> ParseResult<AstNode> {
+        self.parse_binary(1)
+    }
+
+    /// Precedence climbing over binary and assignment operators
+    fn parse_binary(&mut self, min_precedence: u8) -> ParseResult<AstNode> {
+        self.enter()?;
+        let expression = self.parse_binary_operands(min_precedence);
+        self.depth -= 1;
+        expression
+    }
+
+    fn parse_binary_operands(&mut self, min_precedence: u8) -> ParseResult<AstNode> {
+        let mut left = self.parse_unary()?;
+
+        while let Some(op) = self.peek_binary_operator() {
+            let precedence = op.precedence();
+            if precedence < min_precedence {
+                break;
+            }
+
+            let op_position = self.advance().span.start;
+            let next_precedence = if op.is_left_associative() { precedence + 1 } else { precedence };
+            let right = self.parse_binary(next_precedence)?;
+            left = combine(op, op_position, left, right)?;
+        }
+
+        Ok(left)
+    }
+
+    /// The current token if it is an infix operator
+    fn peek_binary_operator(&self) -> Option<Operator> {
+        match &self.peek().token_type {
+            TokenType::Operator(op) if (1..=12).contains(&op.precedence()) => Some(op.clone()),
+            _ => None,
+        }
+    }
+
+    /// Prefix operators, applied innermost first
+    fn parse_unary(&mut self) -> ParseResult<AstNode> {
+        let mut prefixes = Vec::new();
+        while let TokenType::Operator(op @ (Operator::Minus | Operator::Not | Operator::BitNot)) = &self.peek().token_type {
+            let op = op.clone();
+            prefixes.push((op, self.advance().span));
+        }
+
+        let mut expression = self.parse_postfix()?;
+        while let Some((op, start)) = prefixes.pop() {
+            let span = start.merge(expression.span);
+            expression = AstNode::new(NodeKind::Unary { op, operand: Box::new(expression) }, span);
+        }
+
+        Ok(expression)
+    }
+
+    /// Calls, field access and indexing
+    fn parse_postfix(&mut self) -> ParseResult<AstNode> {
+        let mut expression = self.parse_primary()?;
+
+        loop {
+            if self.match_delimiter(Delimiter::LeftParen) {
+                let args = self.parse_arguments()?;
+                expression = self.finish_call(expression, args)?;
+            } else if self.match_operator(Operator::Dot) {
+                let field = self.expect_identifier("field name after '.'")?;
+                let span = self.span_from(expression.span);
+                expression = AstNode::new(NodeKind::FieldAccess { object: Box::new(expression), field }, span);
+            } else if self.match_delimiter(Delimiter::LeftBracket) {
+                let index = self.parse_expression()?;
+                self.expect_delimiter(Delimiter::RightBracket, "']' after index")?;
+                let span = self.span_from(expression.span);
+                expression = AstNode::new(
+                    NodeKind::Index {
+                        object: Box::new(expression),
+                        index: Box::new(index),
+                    },
+                    span,
+                );
+            } else {
+                return Ok(expression);
+            }
+        }
+    }
+
+    /// Parse call arguments after the opening parenthesis
+    fn parse_arguments(&mut self) -> ParseResult<Vec<AstNode>> {
+        let mut args = Vec::new();
+        if !self.check_delimiter(Delimiter::RightParen) {
+            loop {
+                args.push(self.parse_expression()?);
+                if !self.match_delimiter(Delimiter::Comma) {
+                    break;
+                }
+            }
+        }
+        self.expect_delimiter(Delimiter::RightParen, "')' after arguments")?;
+
+        Ok(args)
+    }
+
+    /// Build a call node, turning calls on the state receiver into state accesses
+    fn finish_call(&self, callee: AstNode, args: Vec<AstNode>) -> ParseResult<AstNode> {
+        let span = self.span_from(callee.span);
+
+        if let NodeKind::FieldAccess { object, field } = &callee.kind
+            && matches!(&object.kind, NodeKind::Identifier(name) if name == STATE_RECEIVER)
+        {
+            let operation = StateOperation::from_name(field).ok_or_else(|| {
+                let known: Vec<&str> = StateOperation::ALL.iter().map(StateOperation::name).collect();
+                ParseError::syntax_error(callee.span.start, format!("Unknown state operation '{}', expected one of: {}", field, known.join(", ")))
+            })?;
+
+            if args.len() != operation.arity() {
+                return Err(ParseError::syntax_error(
+                    callee.span.start,
+                    format!("{}.{} takes {} argument(s), found {}", STATE_RECEIVER, operation.name(), operation.arity(), args.len()),
+                ));
+            }
+
+            return Ok(AstNode::new(NodeKind::StateAccess { operation, args }, span));
+        }
+
+        Ok(AstNode::new(NodeKind::Call { callee: Box::new(callee), args }, span))
+    }
+
+    fn parse_primary(&mut self) -> ParseResult<AstNode> {
+        let token = self.peek().clone();
+        let kind = match token.token_type {
+            TokenType::IntegerLiteral(value) => NodeKind::Literal(Literal::Integer(value)),
+            TokenType::FloatLiteral(value) => NodeKind::Literal(Literal::Float(value)),
+            TokenType::StringLiteral(value) => NodeKind::Literal(Literal::String(value)),
+            TokenType::BooleanLiteral(value) => NodeKind::Literal(Literal::Boolean(value)),
+            TokenType::CharLiteral(value) => NodeKind::Literal(Literal::Char(value)),
+            TokenType::Keyword(Keyword::Null) => NodeKind::Literal(Literal::Null),
+            TokenType::Identifier(name) => NodeKind::Identifier(name),
+            TokenType::Delimiter(Delimiter::LeftParen) => return self.parse_group(),
+            _ => return Err(self.error_expected("expression")),
+        };
+        self.advance();
+
+        Ok(AstNode::new(kind, token.span))
+    }
+
+    /// Parenthesized expression, spanning the parentheses
+    fn parse_group(&mut self) -> ParseResult<AstNode> {
+        let start = self.advance().span;
+        let mut expression = self.parse_expression()?;
+        self.expect_delimiter(Delimiter::RightParen, "')' after expression")?;
+        expression.span = self.span_from(start);
+        Ok(expression)
+    }
+}
+
+/// Build the node for `left op right`
+fn combine(op: Operator, op_position: Position, left: AstNode, right: AstNode) -> ParseResult<AstNode> {
+    let span = left.span.merge(right.span);
+
+    if !is_assignment(&op) {
+        let (left, right) = (Box::new(left), Box::new(right));
+        return Ok(AstNode::new(NodeKind::Binary { op, left, right }, span));
+    }
+
+    if !left.is_assignable() {
+        return Err(ParseError::new(ParseErrorKind::InvalidAssignment, op_position, format!("Invalid left-hand side of '{}'", op)));
+    }
+    let (target, value) = (Box::new(left), Box::new(right));
+    Ok(AstNode::new(NodeKind::Assign { op, target, value }, span))
+}
+
+fn is_assignment(op: &Operator) -> bool {
+    matches!(
+        op,
+        Operator::Assign | Operator::PlusAssign | Operator::MinusAssign | Operator::MultiplyAssign | Operator::DivideAssign | Operator::ModuloAssign
+    )
+}
+
+impl SyntaxParser<DotVMAst> for DotVMSyntaxParser {
+    fn parse_tokens(&mut self, tokens: &[Token]) -> ParseResult<DotVMAst> {
+        self.load(tokens);
+
+        let mut items = Vec::new();
+        while !self.is_at_end() {
+            items.push(self.parse_statement()?);
+        }
+
+        Ok(DotVMAst::new(items))
+    }
+
+    fn parse_construct(&mut self) -> ParseResult<DotVMAst> {
+        if self.is_at_end() {
+            return Ok(DotVMAst::default());
+        }
+        Ok(DotVMAst::new(vec![self.parse_statement()?]))
+    }
+
+    fn expect_token(&mut self, expected: &Token) -> ParseResult<()> {
+        if self.consume_if(expected) {
+            Ok(())
+        } else {
+            Err(self.error_expected(&format!("'{}'", expected.lexeme)))
+        }
+    }
+
+    fn consume_if(&mut self, expected: &Token) -> bool {
+        let matched = self.peek().token_type == expected.token_type;
+        if matched {
+            self.advance();
+        }
+        matched
+    }
+
+    fn current_token(&self) -> Option<&Token> {
+        self.tokens.get(self.current)
+    }
+}
+
+impl Default for DotVMSyntaxParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::dotvm::DotVMLexer;
+
+    fn parse(source: &str) -> ParseResult<DotVMAst> {
+        let tokens = DotVMLexer::new().tokenize(source)?;
+        DotVMSyntaxParser::new().parse_tokens(&tokens)
+    }
+
+    fn parse_expression(source: &str) -> AstNode {
+        let ast = parse(&format!("{};", source)).unwrap();
+        match ast.items.into_iter().next().unwrap().kind {
+            NodeKind::Expression(expression) => *expression,
+            other => panic!("expected expression statement, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_let_binding() {
+        let ast = parse("let mut x: i32 = 42;").unwrap();
+        assert_eq!(ast.items.len(), 1);
+
+        let item = &ast.items[0];
+        match &item.kind {
+            NodeKind::Let {
+                name,
+                mutable,
+                type_annotation,
+                value,
+            } => {
+                assert_eq!(name, "x");
+                assert!(*mutable);
+                assert_eq!(type_annotation, &Some(BaseType::I32));
+                assert_eq!(value.as_ref().unwrap().kind, NodeKind::Literal(Literal::Integer(42)));
+            }
+            other => panic!("expected let binding, got {:?}", other),
+        }
+        assert_eq!(item.span, Span::new(Position::new(1, 1), Position::new(1, 21)));
+    }
+
+    #[test]
+    fn test_parse_function_definition() {
+        let ast = parse("fn add(a: i64, b: i64) -> i64 {\n    return a + b;\n}\nlet total = add(1, 2);").unwrap();
+        assert_eq!(ast.functions().count(), 1);
+
+        match &ast.items[0].kind {
+            NodeKind::Function { name, params, return_type, body } => {
+                assert_eq!(name, "add");
+                assert_eq!(params.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(), vec!["a", "b"]);
+                assert_eq!(params[1].param_type, BaseType::I64);
+                assert_eq!(return_type, &Some(BaseType::I64));
+                assert!(matches!(body[0].kind, NodeKind::Return(Some(_))));
+            }
+            other => panic!("expected function, got {:?}", other),
+        }
+        assert_eq!(ast.items[0].span.end, Position::new(3, 2));
+
+        match &ast.items[1].kind {
+            NodeKind::Let { value: Some(value), .. } => {
+                assert!(matches!(&value.kind, NodeKind::Call { args, .. } if args.len() == 2));
+            }
+            other => panic!("expected let binding, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_operator_precedence_and_associativity() {
+        // 1 + (2 * 3)
+        match parse_expression("1 + 2 * 3").kind {
+            NodeKind::Binary { op, right, .. } => {
+                assert_eq!(op, Operator::Plus);
+                assert!(matches!(right.kind, NodeKind::Binary { op: Operator::Multiply, .. }));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // (1 - 2) - 3
+        match parse_expression("1 - 2 - 3").kind {
+            NodeKind::Binary { left, right, .. } => {
+                assert!(matches!(left.kind, NodeKind::Binary { op: Operator::Minus, .. }));
+                assert_eq!(right.kind, NodeKind::Literal(Literal::Integer(3)));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // 2 ** (3 ** 2)
+        match parse_expression("2 ** 3 ** 2").kind {
+            NodeKind::Binary { right, .. } => assert!(matches!(right.kind, NodeKind::Binary { op: Operator::Power, .. })),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // a = (b += 1)
+        match parse_expression("a = b += 1").kind {
+            NodeKind::Assign { op, value, .. } => {
+                assert_eq!(op, Operator::Assign);
+                assert!(matches!(value.kind, NodeKind::Assign { op: Operator::PlusAssign, .. }));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // (-x) * y, with the parentheses spanning the whole group
+        let grouped = parse_expression("(-x * y) && !done");
+        assert_eq!(grouped.span, Span::new(Position::new(1, 1), Position::new(1, 18)));
+        match grouped.kind {
+            NodeKind::Binary { op, left, right } => {
+                assert_eq!(op, Operator::And);
+                assert_eq!(left.span, Span::new(Position::new(1, 1), Position::new(1, 9)));
+                assert!(matches!(left.kind, NodeKind::Binary { op: Operator::Multiply, .. }));
+                assert!(matches!(right.kind, NodeKind::Unary { op: Operator::Not, .. }));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_control_flow() {
+        let source = "
+            while i < 10 {
+                if i % 2 == 0 { continue; } else if i > 7 { break; } else { i += 1; }
+            }
+        ";
+        let ast = parse(source).unwrap();
+
+        let body = match &ast.items[0].kind {
+            NodeKind::While { condition, body } => {
+                assert!(matches!(condition.kind, NodeKind::Binary { op: Operator::Less, .. }));
+                body
+            }
+            other => panic!("expected while, got {:?}", other),
+        };
+
+        match &body[0].kind {
+            NodeKind::If {
+                then_branch,
+                else_branch: Some(else_branch),
+                ..
+            } => {
+                assert_eq!(then_branch[0].kind, NodeKind::Continue);
+                match &else_branch[0].kind {
+                    NodeKind::If {
+                        then_branch, else_branch: Some(last), ..
+                    } => {
+                        assert_eq!(then_branch[0].kind, NodeKind::Break);
+                        assert!(matches!(last[0].kind, NodeKind::Expression(_)));
+                    }
+                    other => panic!("expected else if, got {:?}", other),
+                }
+            }
+            other => panic!("expected if, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_state_access() {
+        let expression = parse_expression(r#"state.store("count", state.load("count") + 1)"#);
+        match expression.kind {
+            NodeKind::StateAccess { operation, args } => {
+                assert_eq!(operation, StateOperation::Store);
+                assert_eq!(args[0].kind, NodeKind::Literal(Literal::String("count".to_string())));
+                match &args[1].kind {
+                    NodeKind::Binary { left, .. } => assert!(matches!(left.kind, NodeKind::StateAccess { operation: StateOperation::Load, .. })),
+                    other => panic!("unexpected {:?}", other),
+                }
+            }
+            other => panic!("expected state access, got {:?}", other),
+        }
+
+        // Other receivers are ordinary method calls
+        assert!(matches!(parse_expression("store.load(1)").kind, NodeKind::Call { .. }));
+
+        let error = parse("state.drop(\"k\");").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::SyntaxError);
+        assert!(error.message.contains("Unknown state operation 'drop'"));
+
+        let error = parse("let v = state.store(\"k\");").unwrap_err();
+        assert!(error.message.contains("state.store takes 2 argument(s), found 1"));
+        assert_eq!(error.position, Position::new(1, 9));
+    }
+
+    #[test]
+    fn test_comments_are_ignored() {
+        let ast = parse("// counter\nlet x = 1; /* block */ let y = 2;").unwrap();
+        assert_eq!(ast.items.len(), 2);
+    }
+
+    #[test]
+    fn test_errors_report_positions() {
+        // A missing ';' points just past the previous token
+        let error = parse("let x = 1\nlet y = 2;").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::ExpectedToken);
+        assert_eq!(error.position, Position::new(1, 10));
+        assert!(error.message.contains("';' after let binding"));
+
+        let error = parse("let = 5;").unwrap_err();
+        assert_eq!(error.position, Position::new(1, 5));
+        assert!(error.message.contains("found '='"));
+
+        let error = parse("fn f() {\n    return 1;\n").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UnexpectedEof);
+        assert_eq!(error.position, Position::new(2, 14));
+
+        let error = parse("1 + 2 = 3;").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::InvalidAssignment);
+        assert_eq!(error.position, Position::new(1, 7));
+    }
+
+    #[test]
+    fn test_recursion_limit() {
+        let nested = format!("let x = {}1{};", "(".repeat(40), ")".repeat(40));
+        let tokens = DotVMLexer::new().tokenize(&nested).unwrap();
+
+        let mut parser = DotVMSyntaxParser::with_config(ParserConfig::new().with_max_recursion_depth(20));
+        let error = parser.parse_tokens(&tokens).unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::RecursionLimitExceeded);
+        assert!(error.position.is_valid());
+
+        // The depth is reset between runs and a larger limit accepts the input
+        parser.set_config(ParserConfig::new().with_max_recursion_depth(100));
+        assert!(parser.parse_tokens(&tokens).is_ok());
+
+        // Prefix operators are applied iteratively and do not count as nesting
+        let unary = format!("let y = {}x;", "!".repeat(50));
+        let tokens = DotVMLexer::new().tokenize(&unary).unwrap();
+        assert!(parser.parse_tokens(&tokens).is_ok());
+    }
+}
@@ -48,14 +48,16 @@
 //! ```
 
 pub mod common;
+pub mod dotvm;
 pub mod traits;
 // TODO: Implement in future phases
-// pub mod dotvm;
 // pub mod validation;
 
 // Re-export commonly used types
 pub use common::{Delimiter, Keyword, Operator, ParseContext, ParseError, ParseErrorKind, ParseResult, ParserConfig, Position, Token, TokenType, position::Span};
 
+pub use dotvm::{AstNode, DotVMAst, DotVMLexer, DotVMParser, DotVMSemanticAnalyzer, DotVMSyntaxParser};
+
 pub use traits::{AstTransformer, AstVisitor, BaseType, ConstantValue, Lexer, Parser, SemanticAnalyzer, SymbolInfo, SymbolTable, SymbolType, SyntaxParser, Validator};
 
 /// Create a default parser configuration
@@ -79,7 +81,5 @@ pub fn dev_config() -> ParserConfig {
 
 /// Quick parse function for simple use cases
 pub fn quick_parse_tokens(source: &str) -> ParseResult<Vec<Token>> {
-    // This is a placeholder - will be implemented when we add the lexer
-    // For now, return an empty token list
-    Ok(vec![Token::new(TokenType::Eof, "".to_string(), Span::single(Position::start()))])
+    DotVMLexer::new().tokenize(source)
 }
@@ -189,6 +189,7 @@ impl TranspilationConfig {
             "simd" => self.feature_flags.enable_simd,
             "threads" => self.feature_flags.enable_threads,
             "bulk_memory" => self.feature_flags.enable_bulk_memory,
+            "multi_memory" => self.feature_flags.enable_multi_memory,
             "reference_types" => self.feature_flags.enable_reference_types,
             "tail_call" => self.feature_flags.enable_tail_call,
             "multi_value" => self.feature_flags.enable_multi_value,
@@ -391,6 +392,8 @@ pub struct FeatureFlags {
     pub enable_threads: bool,
     /// Enable bulk memory operations
     pub enable_bulk_memory: bool,
+    /// Enable multiple linear memories
+    pub enable_multi_memory: bool,
     /// Enable reference types
     pub enable_reference_types: bool,
     /// Enable tail call optimization
@@ -409,6 +412,7 @@ impl Default for FeatureFlags {
             enable_simd: true,
             enable_threads: false, // Disabled by default due to complexity
             enable_bulk_memory: true,
+            enable_multi_memory: true,
             enable_reference_types: true,
             enable_tail_call: true,
             enable_multi_value: true,
@@ -425,6 +429,7 @@ impl FeatureFlags {
             enable_simd: false,
             enable_threads: false,
             enable_bulk_memory: false,
+            enable_multi_memory: false,
             enable_reference_types: false,
             enable_tail_call: false,
             enable_multi_value: false,
@@ -439,6 +444,7 @@ impl FeatureFlags {
             enable_simd: true,
             enable_threads: false,
             enable_bulk_memory: true,
+            enable_multi_memory: true,
             enable_reference_types: true,
             enable_tail_call: true,
             enable_multi_value: true,
@@ -453,6 +459,7 @@ impl FeatureFlags {
             enable_simd: true,
             enable_threads: true,
             enable_bulk_memory: true,
+            enable_multi_memory: true,
             enable_reference_types: true,
             enable_tail_call: true,
             enable_multi_value: true,
@@ -22,7 +22,11 @@
 use super::{
     config::TranspilationConfig,
     error::{TranspilationError, TranspilationResult},
-    pipeline::{TranspilationPipeline, pipeline_builder::PipelineBuilder},
+    pipeline::{
+        TranspilationPipeline,
+        pipeline_builder::PipelineBuilder,
+        streaming::{FunctionSink, StreamingOptions, StreamingOutput},
+    },
     types::TranspiledModule,
 };
 use dotvm_core::bytecode::VmArchitecture;
@@ -79,6 +83,15 @@ impl NewTranspilationEngine {
         self.pipeline.execute(wasm_bytes)
     }
 
+    /// Transpile a WASM stream, writing each function to `sink` as soon as it is translated
+    ///
+    /// Use this for modules too large to hold in memory; batch size and worker threads follow
+    /// the pipeline configuration.
+    pub fn transpile_streaming<R: std::io::Read, S: FunctionSink>(&mut self, reader: R, sink: &mut S) -> TranspilationResult<StreamingOutput> {
+        let options = StreamingOptions::from_config(&self.config);
+        self.pipeline.execute_streaming(reader, sink, &options)
+    }
+
     /// Get the engine configuration
     pub fn config(&self) -> &TranspilationConfig {
         &self.config
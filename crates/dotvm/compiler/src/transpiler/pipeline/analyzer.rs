@@ -222,15 +222,18 @@ impl Analyzer {
                     //     }
                     // }
 
-                    // Bulk memory instructions would be detected here
-                    // WasmInstruction::MemoryCopy { .. } |
-                    // WasmInstruction::MemoryFill { .. } => {
-                    //     if !required_features.contains(&"bulk_memory".to_string()) {
-                    //         required_features.push("bulk_memory".to_string());
-                    //     }
-                    // }
+                    // Bulk memory instructions
+                    WasmInstruction::MemoryCopy { .. } | WasmInstruction::MemoryFill { .. } | WasmInstruction::MemoryInit { .. } | WasmInstruction::DataDrop { .. } => {
+                        if !required_features.contains(&"bulk_memory".to_string()) {
+                            required_features.push("bulk_memory".to_string());
+                        }
+                    }
                     _ => {}
                 }
+
+                if instruction.memory_indices().iter().any(|&memory| memory != 0) && !required_features.contains(&"multi_memory".to_string()) {
+                    required_features.push("multi_memory".to_string());
+                }
             }
         }
 
@@ -289,7 +292,7 @@ impl Analyzer {
     }
 
     /// Analyze a single function
-    fn analyze_function(&self, index: u32, function: &WasmFunction) -> TranspilationResult<FunctionAnalysis> {
+    pub(super) fn analyze_function(&self, index: u32, function: &WasmFunction) -> TranspilationResult<FunctionAnalysis> {
         let mut analysis = FunctionAnalysis {
             index,
             complexity_score: 0,
@@ -402,15 +405,13 @@ impl Analyzer {
                 is_aligned: memarg.align >= 3,
                 frequency: 1,
             }),
-            // Bulk memory operations would be handled here
-            // WasmInstruction::MemoryCopy { .. } |
-            // WasmInstruction::MemoryFill { .. } => Some(MemoryAccessInfo {
-            //     instruction_index: inst_index,
-            //     access_type: MemoryAccessType::Bulk,
-            //     size: 0, // Variable size
-            //     is_aligned: false, // Conservative assumption
-            //     frequency: 1,
-            // }),
+            WasmInstruction::MemoryCopy { .. } | WasmInstruction::MemoryFill { .. } | WasmInstruction::MemoryInit { .. } => Some(MemoryAccessInfo {
+                instruction_index: inst_index,
+                access_type: MemoryAccessType::Bulk,
+                size: 0,           // Length is only known at runtime
+                is_aligned: false, // Conservative assumption
+                frequency: 1,
+            }),
             _ => None,
         }
     }
@@ -614,7 +615,7 @@ impl Analyzer {
     }
 
     /// Generate performance profile
-    fn generate_performance_profile(&self, module: &WasmModule, function_analyses: &[FunctionAnalysis]) -> PerformanceProfile {
+    pub(super) fn generate_performance_profile(&self, module: &WasmModule, function_analyses: &[FunctionAnalysis]) -> PerformanceProfile {
         let mut profile = PerformanceProfile::default();
 
         // Calculate total estimated cycles
@@ -23,6 +23,7 @@ pub mod analyzer;
 pub mod pipeline_builder;
 pub mod postprocessor;
 pub mod preprocessor;
+pub mod streaming;
 pub mod translator;
 
 use super::{
@@ -203,6 +204,66 @@ impl TranspilationPipeline {
         Ok(result)
     }
 
+    /// Execute the pipeline over a WASM stream, writing each function to `sink` as it is translated
+    ///
+    /// Only the functions of the current batch are held in memory. Whole-module passes that need
+    /// every function at once (function reordering, cross-function inlining) are skipped and
+    /// recorded as warnings.
+    pub fn execute_streaming<R: std::io::Read, S: streaming::FunctionSink>(
+        &mut self,
+        reader: R,
+        sink: &mut S,
+        options: &streaming::StreamingOptions,
+    ) -> TranspilationResult<streaming::StreamingOutput> {
+        options.validate()?;
+        let start_time = std::time::Instant::now();
+
+        // Stages 1-4 per function: parse, analyze, translate and optimize as bodies arrive
+        let mut stream = streaming::FunctionStream::new(&self.analyzer, &self.postprocessor, &self.config, sink, options)?;
+        let stage_start = std::time::Instant::now();
+        let module = self.preprocessor.execute_streaming(reader, options.read_chunk_size, |index, function| stream.push(index, function))?;
+        let preprocessing_time = stage_start.elapsed().saturating_sub(stream.timings().busy());
+        let (summaries, mut timings, peak_batch_bytes) = stream.finish()?;
+        let function_count = summaries.len();
+
+        // Module-level translation of everything except function bodies
+        let stage_start = std::time::Instant::now();
+        let mut translated = self
+            .translator
+            .translate_module_shell(&module, &self.config)
+            .map_err(|e| TranspilationError::translation_error("translation", format!("Translation failed: {}", e)))?;
+        let performance_profile = self.analyzer.generate_performance_profile(&module, &summaries);
+        translator::Translator::apply_analysis_metadata(&mut translated, &summaries, &performance_profile);
+        timings.translation += stage_start.elapsed();
+
+        // Module-level postprocessing that does not need the function bodies
+        let stage_start = std::time::Instant::now();
+        let skipped_passes: Vec<String> = self.postprocessor.whole_module_passes(&self.config).into_iter().map(String::from).collect();
+        for pass in &skipped_passes {
+            self.context.add_warning(format!("Streaming mode: skipped whole-module pass '{}'", pass));
+        }
+        self.postprocessor
+            .finish_streamed_module(&mut translated, function_count, &self.config)
+            .map_err(|e| TranspilationError::translation_error("postprocessing", format!("Postprocessing failed: {}", e)))?;
+        timings.postprocessing += stage_start.elapsed();
+
+        self.context.record_stage_time("preprocessing", preprocessing_time);
+        self.context.record_stage_time("analysis", timings.analysis);
+        self.context.record_stage_time("translation", timings.translation);
+        self.context.record_stage_time("postprocessing", timings.postprocessing);
+        for stage in ["preprocessing", "analysis", "translation", "postprocessing"] {
+            self.context.metrics.record_processed_items(stage, function_count);
+        }
+        self.context.metrics.record_memory_usage("translation", peak_batch_bytes);
+        self.context.metrics.stage_times.insert("total".to_string(), start_time.elapsed());
+
+        Ok(streaming::StreamingOutput {
+            module: translated,
+            functions_written: function_count,
+            skipped_passes,
+        })
+    }
+
     /// Execute a single pipeline stage with timing and error handling
     fn execute_stage<S: PipelineStage>(&mut self, stage: &mut S, input: S::Input, stage_name: &str) -> TranspilationResult<S::Output> {
         // Check if stage can be skipped
@@ -329,4 +390,74 @@ mod tests {
         let pipeline = TranspilationPipeline::new(config);
         assert!(pipeline.is_ok());
     }
+
+    /// Two `() -> i32` functions computing `1 + 2`
+    const TWO_FUNCTIONS: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+        0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type section
+        0x03, 0x03, 0x02, 0x00, 0x00, // function section
+        0x0a, 0x11, 0x02, // code section
+        0x07, 0x00, 0x41, 0x01, 0x41, 0x02, 0x6a, 0x0b, // function 0
+        0x07, 0x00, 0x41, 0x01, 0x41, 0x02, 0x6a, 0x0b, // function 1
+    ];
+
+    #[test]
+    fn test_streaming_writes_functions_to_sink() {
+        let mut pipeline = TranspilationPipeline::new(TranspilationConfig::default()).unwrap();
+        let options = streaming::StreamingOptions {
+            read_chunk_size: 4,
+            ..Default::default()
+        };
+
+        let mut sink = Vec::new();
+        let output = pipeline.execute_streaming(std::io::Cursor::new(TWO_FUNCTIONS), &mut sink, &options).unwrap();
+
+        assert_eq!(output.functions_written, 2);
+        assert_eq!(sink.len(), 2);
+        assert!(output.module.functions.is_empty());
+
+        let metrics = &pipeline.context().metrics;
+        for stage in ["preprocessing", "analysis", "translation", "postprocessing", "total"] {
+            assert!(metrics.stage_times.contains_key(stage), "missing timing for {}", stage);
+        }
+        assert_eq!(metrics.processed_items.get("translation"), Some(&2));
+    }
+
+    #[test]
+    fn test_streaming_skips_whole_module_passes() {
+        let mut pipeline = TranspilationPipeline::new(TranspilationConfig::default()).unwrap();
+
+        let output = pipeline
+            .execute_streaming(std::io::Cursor::new(TWO_FUNCTIONS), &mut Vec::new(), &streaming::StreamingOptions::default())
+            .unwrap();
+
+        assert!(output.skipped_passes.iter().any(|pass| pass == "function_ordering"));
+        assert!(pipeline.context().warnings.iter().any(|warning| warning.contains("function_ordering")));
+    }
+
+    #[test]
+    fn test_streaming_parallel_batches_preserve_order() {
+        let mut pipeline = TranspilationPipeline::new(TranspilationConfig::default()).unwrap();
+        let options = streaming::StreamingOptions {
+            batch_size: 2,
+            worker_threads: 2,
+            ..Default::default()
+        };
+
+        let mut sink = Vec::new();
+        pipeline.execute_streaming(std::io::Cursor::new(TWO_FUNCTIONS), &mut sink, &options).unwrap();
+
+        let names: Vec<_> = sink.iter().map(|function| function.name.as_str()).collect();
+        assert_eq!(names, ["func_0", "func_1"]);
+    }
+
+    #[test]
+    fn test_streaming_options_validation() {
+        let options = streaming::StreamingOptions {
+            batch_size: 0,
+            ..Default::default()
+        };
+        assert!(options.validate().is_err());
+        assert!(streaming::StreamingOptions::default().validate().is_ok());
+    }
 }
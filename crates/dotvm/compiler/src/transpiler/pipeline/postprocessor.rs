@@ -24,6 +24,7 @@ use super::{
     },
     PipelineStage,
 };
+use crate::optimizer::Optimizer;
 
 /// Postprocessor stage for final optimizations and validation
 pub struct Postprocessor {
@@ -43,21 +44,29 @@ impl Postprocessor {
     }
 
     /// Optimize the transpiled module
-    fn optimize_module(&self, module: &mut TranspiledModule, config: &TranspilationConfig) -> TranspilationResult<()> {
+    fn optimize_module(&self, module: TranspiledModule, config: &TranspilationConfig) -> TranspilationResult<TranspiledModule> {
         if !self.enable_optimizations {
-            return Ok(());
+            return Ok(module);
         }
 
+        // Inline small functions, drop those left unreachable, then run the peephole rules
+        let level = config.effective_optimization_level();
+        let mut module = if level.enables_optimization("dead_code_elimination") {
+            Optimizer::new(config.target_architecture, level.as_u8()).optimize_module(module)
+        } else {
+            module
+        };
+
         // Function-level optimizations
         for function in &mut module.functions {
             self.optimize_function(function, config)?;
         }
 
         // Module-level optimizations
-        self.optimize_function_ordering(module, config)?;
-        self.optimize_memory_layout(module, config)?;
+        self.optimize_function_ordering(&mut module, config)?;
+        self.optimize_memory_layout(&mut module, config)?;
 
-        Ok(())
+        Ok(module)
     }
 
     /// Optimize a single function
@@ -120,15 +129,12 @@ impl Postprocessor {
         while i < function.instructions.len() {
             let current = &function.instructions[i];
 
-            // Look for patterns like: load X, store X (redundant store)
+            // Look for patterns like: get X, set X, which leave the variable and the stack unchanged
             if i + 1 < function.instructions.len() {
                 let next = &function.instructions[i + 1];
 
-                // Check for load followed by immediate store to same location
                 if self.is_redundant_load_store_pair(current, next) {
-                    // Skip the redundant store
-                    optimized_instructions.push(current.clone());
-                    i += 2; // Skip both instructions for now (simplified)
+                    i += 2;
                     continue;
                 }
             }
@@ -141,10 +147,12 @@ impl Postprocessor {
         Ok(())
     }
 
-    /// Check if two instructions form a redundant load-store pair
+    /// Check if two instructions write a variable back with the value just read from it
+    ///
+    /// Memory loads and stores are never paired: the store's address comes from the stack,
+    /// so it cannot be known to match the load's.
     fn is_redundant_load_store_pair(&self, first: &crate::transpiler::types::TranspiledInstruction, second: &crate::transpiler::types::TranspiledInstruction) -> bool {
-        // Simplified check - in practice this would be more sophisticated
-        first.opcode.contains("load") && second.opcode.contains("store")
+        matches!((first.opcode.as_str(), second.opcode.as_str()), ("local.get", "local.set") | ("global.get", "global.set")) && first.operands == second.operands
     }
 
     /// Apply constant folding
@@ -317,11 +325,16 @@ impl Postprocessor {
             }
         }
 
+        self.validate_module_shell(module, module.functions.len(), config)
+    }
+
+    /// Validate exports and architecture against a module with `function_count` functions
+    fn validate_module_shell(&self, module: &TranspiledModule, function_count: usize, config: &TranspilationConfig) -> TranspilationResult<()> {
         // Validate exports reference valid indices
         for export in &module.exports {
             match export.kind {
                 crate::transpiler::types::ExportKind::Function => {
-                    if export.index as usize >= module.functions.len() {
+                    if export.index as usize >= function_count {
                         return Err(TranspilationError::postprocessing_error(
                             "validation",
                             format!("Export '{}' references non-existent function {}", export.name, export.index),
@@ -350,15 +363,49 @@ impl Postprocessor {
 
         Ok(())
     }
+
+    /// Optimize a function translated in streaming mode
+    pub(super) fn optimize_streamed_function(&self, function: &mut crate::transpiler::types::TranspiledFunction, config: &TranspilationConfig) -> TranspilationResult<()> {
+        if !self.enable_optimizations {
+            return Ok(());
+        }
+
+        self.optimize_function(function, config)
+    }
+
+    /// Module-level passes that need every function at once and therefore cannot run in streaming mode
+    pub(super) fn whole_module_passes(&self, config: &TranspilationConfig) -> Vec<&'static str> {
+        let mut passes = Vec::new();
+        if self.enable_optimizations {
+            passes.push("function_ordering");
+        }
+        if config.effective_optimization_level().enables_optimization("function_inlining") {
+            passes.push("function_inlining");
+        }
+        passes
+    }
+
+    /// Finish a module whose `function_count` functions were already written out by streaming mode
+    pub(super) fn finish_streamed_module(&self, module: &mut TranspiledModule, function_count: usize, config: &TranspilationConfig) -> TranspilationResult<()> {
+        if self.enable_optimizations {
+            self.optimize_memory_layout(module, config)?;
+        }
+
+        if self.validate_output {
+            self.validate_module_shell(module, function_count, config)?;
+        }
+
+        Ok(())
+    }
 }
 
 impl PipelineStage for Postprocessor {
     type Input = TranspiledModule;
     type Output = TranspiledModule;
 
-    fn execute(&mut self, mut input: Self::Input, config: &TranspilationConfig) -> TranspilationResult<Self::Output> {
+    fn execute(&mut self, input: Self::Input, config: &TranspilationConfig) -> TranspilationResult<Self::Output> {
         // Apply optimizations
-        self.optimize_module(&mut input, config)?;
+        let input = self.optimize_module(input, config)?;
 
         // Validate the result
         if self.validate_output {
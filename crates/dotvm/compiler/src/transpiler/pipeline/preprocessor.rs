@@ -23,7 +23,11 @@ use super::{
     },
     PipelineStage,
 };
-use crate::wasm::{ast::WasmModule, parser::WasmParser};
+use crate::wasm::{
+    ast::{WasmFunction, WasmModule},
+    parser::{ParserConfig, WasmParser},
+};
+use std::io::Read;
 
 /// Preprocessor stage for input validation and normalization
 pub struct Preprocessor {
@@ -37,7 +41,11 @@ impl Preprocessor {
     /// Create a new preprocessor
     pub fn new(config: &TranspilationConfig) -> TranspilationResult<Self> {
         Ok(Self {
-            parser: WasmParser::new(),
+            parser: WasmParser::with_config(
+                ParserConfig::default()
+                    .with_bulk_memory(config.feature_flags.enable_bulk_memory)
+                    .with_multi_memory(config.feature_flags.enable_multi_memory),
+            ),
             validation_config: ValidationConfig::from_transpilation_config(config),
         })
     }
@@ -79,16 +87,21 @@ impl Preprocessor {
 
     /// Validate parsed WASM module structure
     fn validate_module_structure(&self, module: &WasmModule) -> TranspilationResult<()> {
-        // Check function count limits
-        if let Some(max_functions) = self.validation_config.max_functions {
-            if module.functions.len() > max_functions {
-                return Err(TranspilationError::preprocessing_error(
-                    "structure_validation",
-                    format!("Too many functions: {} (max: {})", module.functions.len(), max_functions),
-                ));
-            }
+        self.validate_module_limits(module, module.functions.len())?;
+
+        // Validate individual functions
+        for (index, function) in module.functions.iter().enumerate() {
+            self.validate_function(index as u32, function)?;
         }
 
+        Ok(())
+    }
+
+    /// Validate module-level limits, exports and imports against `function_count` defined functions
+    fn validate_module_limits(&self, module: &WasmModule, function_count: usize) -> TranspilationResult<()> {
+        // Check function count limits
+        self.validation_config.validate_function_count(function_count)?;
+
         // Check global count limits
         if let Some(max_globals) = self.validation_config.max_globals {
             if module.globals.len() > max_globals {
@@ -101,21 +114,16 @@ impl Preprocessor {
 
         // Check memory limits
         if let Some(max_memories) = self.validation_config.max_memories {
-            if module.memories.len() > max_memories {
+            if module.total_memory_count() > max_memories {
                 return Err(TranspilationError::preprocessing_error(
                     "structure_validation",
-                    format!("Too many memories: {} (max: {})", module.memories.len(), max_memories),
+                    format!("Too many memories: {} (max: {})", module.total_memory_count(), max_memories),
                 ));
             }
         }
 
-        // Validate individual functions
-        for (index, function) in module.functions.iter().enumerate() {
-            self.validate_function(index as u32, function)?;
-        }
-
         // Validate exports
-        self.validate_exports(module)?;
+        self.validate_exports(module, function_count)?;
 
         // Validate imports
         self.validate_imports(module)?;
@@ -124,42 +132,12 @@ impl Preprocessor {
     }
 
     /// Validate a single function
-    fn validate_function(&self, index: u32, function: &crate::wasm::ast::WasmFunction) -> TranspilationResult<()> {
-        // Check function size limits
-        if let Some(max_instructions) = self.validation_config.max_function_instructions {
-            if function.body.len() > max_instructions {
-                return Err(TranspilationError::preprocessing_error(
-                    "function_validation",
-                    format!("Function {} too large: {} instructions (max: {})", index, function.body.len(), max_instructions),
-                ));
-            }
-        }
-
-        // Check parameter count
-        if let Some(max_params) = self.validation_config.max_function_params {
-            if function.signature.params.len() > max_params {
-                return Err(TranspilationError::preprocessing_error(
-                    "function_validation",
-                    format!("Function {} has too many parameters: {} (max: {})", index, function.signature.params.len(), max_params),
-                ));
-            }
-        }
-
-        // Check local variable count
-        if let Some(max_locals) = self.validation_config.max_function_locals {
-            if function.locals.len() > max_locals {
-                return Err(TranspilationError::preprocessing_error(
-                    "function_validation",
-                    format!("Function {} has too many locals: {} (max: {})", index, function.locals.len(), max_locals),
-                ));
-            }
-        }
-
-        Ok(())
+    fn validate_function(&self, index: u32, function: &WasmFunction) -> TranspilationResult<()> {
+        self.validation_config.validate_function(index, function)
     }
 
     /// Validate module exports
-    fn validate_exports(&self, module: &WasmModule) -> TranspilationResult<()> {
+    fn validate_exports(&self, module: &WasmModule, function_count: usize) -> TranspilationResult<()> {
         // Check for duplicate export names
         let mut export_names = std::collections::HashSet::new();
         for export in &module.exports {
@@ -172,7 +150,7 @@ impl Preprocessor {
         for export in &module.exports {
             match export.kind {
                 crate::wasm::ast::WasmExportKind::Function => {
-                    if export.index as usize >= module.functions.len() {
+                    if export.index as usize >= function_count {
                         return Err(TranspilationError::preprocessing_error(
                             "export_validation",
                             format!("Export '{}' references non-existent function {}", export.name, export.index),
@@ -188,7 +166,7 @@ impl Preprocessor {
                     }
                 }
                 crate::wasm::ast::WasmExportKind::Memory => {
-                    if export.index as usize >= module.memories.len() {
+                    if export.index as usize >= module.total_memory_count() {
                         return Err(TranspilationError::preprocessing_error(
                             "export_validation",
                             format!("Export '{}' references non-existent memory {}", export.name, export.index),
@@ -286,6 +264,36 @@ impl Preprocessor {
 
         Ok(module)
     }
+
+    /// Parse and validate a WASM stream, handing each function to `on_function` as soon as it is decoded
+    ///
+    /// Functions are validated individually before being handed over and are not retained, so
+    /// the returned module has no function bodies. The total input size limit does not apply
+    /// in streaming mode.
+    pub fn execute_streaming<R, F>(&mut self, reader: R, chunk_size: usize, mut on_function: F) -> TranspilationResult<WasmModule>
+    where
+        R: Read,
+        F: FnMut(u32, WasmFunction) -> TranspilationResult<()>,
+    {
+        let validation_config = &self.validation_config;
+        let mut function_count = 0usize;
+
+        let module = self
+            .parser
+            .parse_streaming(reader, chunk_size, |index, function| {
+                function_count += 1;
+                validation_config.validate_function_count(function_count)?;
+                validation_config.validate_function(index, &function)?;
+                on_function(index, function)
+            })
+            .map_err(|e| match e {
+                TranspilationError::WasmError(e) => TranspilationError::preprocessing_error("parsing", format!("Failed to parse WASM binary: {}", e)),
+                e => e,
+            })?;
+
+        self.validate_module_limits(&module, function_count)?;
+        self.normalize_module(module)
+    }
 }
 
 impl PipelineStage for Preprocessor {
@@ -358,7 +366,8 @@ impl ValidationConfig {
             max_input_size: Some(64 * 1024 * 1024), // 64MB default limit
             max_functions: Some(10000),             // Reasonable limit
             max_globals: Some(1000),
-            max_memories: Some(1), // WASM spec allows only 1 memory currently
+            // A single memory unless the multi-memory proposal is enabled
+            max_memories: if config.feature_flags.enable_multi_memory { None } else { Some(1) },
             max_function_instructions: config.max_function_size.map(|s| s as usize),
             max_function_params: Some(100),
             max_function_locals: Some(1000),
@@ -367,6 +376,55 @@ impl ValidationConfig {
             remove_unused_elements: config.enable_optimizations,
         }
     }
+
+    /// Check the number of defined functions against the limit
+    fn validate_function_count(&self, function_count: usize) -> TranspilationResult<()> {
+        if let Some(max_functions) = self.max_functions {
+            if function_count > max_functions {
+                return Err(TranspilationError::preprocessing_error(
+                    "structure_validation",
+                    format!("Too many functions: {} (max: {})", function_count, max_functions),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validate a single function
+    fn validate_function(&self, index: u32, function: &WasmFunction) -> TranspilationResult<()> {
+        // Check function size limits
+        if let Some(max_instructions) = self.max_function_instructions {
+            if function.body.len() > max_instructions {
+                return Err(TranspilationError::preprocessing_error(
+                    "function_validation",
+                    format!("Function {} too large: {} instructions (max: {})", index, function.body.len(), max_instructions),
+                ));
+            }
+        }
+
+        // Check parameter count
+        if let Some(max_params) = self.max_function_params {
+            if function.signature.params.len() > max_params {
+                return Err(TranspilationError::preprocessing_error(
+                    "function_validation",
+                    format!("Function {} has too many parameters: {} (max: {})", index, function.signature.params.len(), max_params),
+                ));
+            }
+        }
+
+        // Check local variable count
+        if let Some(max_locals) = self.max_function_locals {
+            if function.locals.len() > max_locals {
+                return Err(TranspilationError::preprocessing_error(
+                    "function_validation",
+                    format!("Function {} has too many locals: {} (max: {})", index, function.locals.len(), max_locals),
+                ));
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -406,6 +464,10 @@ mod tests {
 
         assert!(validation_config.normalize_exports);
         assert!(validation_config.normalize_imports);
-        assert_eq!(validation_config.max_memories, Some(1));
+        assert_eq!(validation_config.max_memories, None);
+
+        let mut single_memory = TranspilationConfig::default();
+        single_memory.feature_flags.enable_multi_memory = false;
+        assert_eq!(ValidationConfig::from_transpilation_config(&single_memory).max_memories, Some(1));
     }
 }
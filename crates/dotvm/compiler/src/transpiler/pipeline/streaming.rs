@@ -0,0 +1,293 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Streaming transpilation for large WASM modules
+//!
+//! The batch pipeline keeps the whole module, and every translated function,
+//! in memory at once. Streaming mode decodes the code section one function
+//! body at a time, translates functions in bounded batches and hands each
+//! result to a [`FunctionSink`] as soon as it is ready, so peak memory follows
+//! the largest function rather than the size of the module.
+
+use super::{
+    super::{
+        config::TranspilationConfig,
+        error::{TranspilationError, TranspilationResult},
+        processors::FunctionProcessor,
+        types::{TranspiledFunction, TranspiledModule},
+    },
+    analyzer::{Analyzer, FunctionAnalysis},
+    postprocessor::Postprocessor,
+};
+use crate::wasm::ast::{WasmFunction, WasmInstruction};
+use std::time::{Duration, Instant};
+
+/// Default number of bytes requested from the reader at a time
+pub const DEFAULT_READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Destination for functions produced by streaming transpilation
+pub trait FunctionSink {
+    /// Accept a translated function; functions arrive in module order
+    fn write_function(&mut self, index: u32, function: TranspiledFunction) -> TranspilationResult<()>;
+
+    /// Called once after the last function has been written
+    fn finish(&mut self) -> TranspilationResult<()> {
+        Ok(())
+    }
+}
+
+impl FunctionSink for Vec<TranspiledFunction> {
+    fn write_function(&mut self, _index: u32, function: TranspiledFunction) -> TranspilationResult<()> {
+        self.push(function);
+        Ok(())
+    }
+}
+
+/// Options controlling streaming transpilation
+#[derive(Debug, Clone)]
+pub struct StreamingOptions {
+    /// Bytes requested from the reader per read
+    pub read_chunk_size: usize,
+    /// Number of functions translated together before being written to the sink
+    pub batch_size: usize,
+    /// Worker threads used to translate a batch
+    pub worker_threads: usize,
+}
+
+impl Default for StreamingOptions {
+    fn default() -> Self {
+        Self {
+            read_chunk_size: DEFAULT_READ_CHUNK_SIZE,
+            batch_size: 1,
+            worker_threads: 1,
+        }
+    }
+}
+
+impl StreamingOptions {
+    /// Derive options from the pipeline configuration
+    ///
+    /// With parallel processing enabled, each batch holds one function per worker thread.
+    pub fn from_config(config: &TranspilationConfig) -> Self {
+        if !config.pipeline_config.enable_parallel_processing {
+            return Self::default();
+        }
+
+        let workers = config
+            .pipeline_config
+            .worker_threads
+            .unwrap_or_else(|| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1));
+
+        Self {
+            read_chunk_size: DEFAULT_READ_CHUNK_SIZE,
+            batch_size: workers,
+            worker_threads: workers,
+        }
+    }
+
+    /// Validate the streaming options
+    pub fn validate(&self) -> TranspilationResult<()> {
+        for (field, value) in [("read_chunk_size", self.read_chunk_size), ("batch_size", self.batch_size), ("worker_threads", self.worker_threads)] {
+            if value == 0 {
+                return Err(TranspilationError::ConfigurationValidationError {
+                    field: format!("streaming.{}", field),
+                    details: "Value cannot be zero".to_string(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Result of a streaming transpilation
+#[derive(Debug, Clone)]
+pub struct StreamingOutput {
+    /// Module header, globals, memory layout, exports, imports and metadata
+    ///
+    /// Function bodies were written to the sink and are not part of this module.
+    pub module: TranspiledModule,
+    /// Number of functions written to the sink
+    pub functions_written: usize,
+    /// Whole-module passes skipped because they need every function at once
+    pub skipped_passes: Vec<String>,
+}
+
+/// Time spent in each stage while functions were streamed
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct StreamTimings {
+    pub analysis: Duration,
+    pub translation: Duration,
+    pub postprocessing: Duration,
+}
+
+impl StreamTimings {
+    /// Total time spent outside the parser
+    pub fn busy(&self) -> Duration {
+        self.analysis + self.translation + self.postprocessing
+    }
+}
+
+/// Per-function driver: analyzes, translates, optimizes and writes functions in batches
+pub(super) struct FunctionStream<'a, S: FunctionSink> {
+    analyzer: &'a Analyzer,
+    postprocessor: &'a Postprocessor,
+    config: &'a TranspilationConfig,
+    sink: &'a mut S,
+    /// One processor per worker thread
+    workers: Vec<FunctionProcessor>,
+    batch_size: usize,
+    batch: Vec<(u32, WasmFunction)>,
+    /// Analyses with per-instruction detail dropped, kept for module metadata
+    summaries: Vec<FunctionAnalysis>,
+    timings: StreamTimings,
+    /// Largest batch held in memory, in bytes of WASM instructions
+    peak_batch_bytes: usize,
+}
+
+impl<'a, S: FunctionSink> FunctionStream<'a, S> {
+    /// Create a function stream writing to `sink`
+    pub fn new(analyzer: &'a Analyzer, postprocessor: &'a Postprocessor, config: &'a TranspilationConfig, sink: &'a mut S, options: &StreamingOptions) -> TranspilationResult<Self> {
+        let workers = (0..options.worker_threads).map(|_| FunctionProcessor::new(config)).collect::<TranspilationResult<Vec<_>>>()?;
+
+        Ok(Self {
+            analyzer,
+            postprocessor,
+            config,
+            sink,
+            workers,
+            batch_size: options.batch_size,
+            batch: Vec::with_capacity(options.batch_size),
+            summaries: Vec::new(),
+            timings: StreamTimings::default(),
+            peak_batch_bytes: 0,
+        })
+    }
+
+    /// Queue a decoded function, translating the batch once it is full
+    pub fn push(&mut self, index: u32, function: WasmFunction) -> TranspilationResult<()> {
+        self.batch.push((index, function));
+        if self.batch.len() >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Time spent so far in analysis, translation and postprocessing
+    pub fn timings(&self) -> StreamTimings {
+        self.timings
+    }
+
+    /// Flush the last partial batch and close the sink
+    ///
+    /// Returns the function summaries, stage timings and the largest batch size in bytes.
+    pub fn finish(mut self) -> TranspilationResult<(Vec<FunctionAnalysis>, StreamTimings, usize)> {
+        self.flush()?;
+
+        let start = Instant::now();
+        self.sink
+            .finish()
+            .map_err(|e| TranspilationError::translation_error("postprocessing", format!("Postprocessing failed: {}", e)))?;
+        self.timings.postprocessing += start.elapsed();
+
+        Ok((self.summaries, self.timings, self.peak_batch_bytes))
+    }
+
+    /// Analyze, translate, optimize and write the queued functions
+    fn flush(&mut self) -> TranspilationResult<()> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+
+        let batch = std::mem::replace(&mut self.batch, Vec::with_capacity(self.batch_size));
+        let batch_bytes = batch.iter().map(|(_, function)| function.body.len() * std::mem::size_of::<WasmInstruction>()).sum();
+        self.peak_batch_bytes = self.peak_batch_bytes.max(batch_bytes);
+
+        let start = Instant::now();
+        let analyses = batch
+            .iter()
+            .map(|(index, function)| self.analyzer.analyze_function(*index, function))
+            .collect::<TranspilationResult<Vec<_>>>()
+            .map_err(|e| TranspilationError::translation_error("analysis", format!("Analysis failed: {}", e)))?;
+        self.timings.analysis += start.elapsed();
+
+        let start = Instant::now();
+        let translated = self
+            .translate(&batch, &analyses)
+            .map_err(|e| TranspilationError::translation_error("translation", format!("Translation failed: {}", e)))?;
+        self.timings.translation += start.elapsed();
+
+        // Release the WASM bodies before the translated functions are handed over
+        drop(batch);
+
+        let start = Instant::now();
+        for ((index, mut function), analysis) in translated.into_iter().zip(analyses) {
+            self.postprocessor
+                .optimize_streamed_function(&mut function, self.config)
+                .and_then(|()| self.sink.write_function(index, function))
+                .map_err(|e| TranspilationError::translation_error("postprocessing", format!("Postprocessing failed: {}", e)))?;
+
+            self.summaries.push(FunctionAnalysis {
+                memory_accesses: Vec::new(),
+                function_calls: Vec::new(),
+                optimization_opportunities: Vec::new(),
+                ..analysis
+            });
+        }
+        self.timings.postprocessing += start.elapsed();
+
+        Ok(())
+    }
+
+    /// Translate a batch, splitting it across the worker processors
+    fn translate(&mut self, batch: &[(u32, WasmFunction)], analyses: &[FunctionAnalysis]) -> TranspilationResult<Vec<(u32, TranspiledFunction)>> {
+        let config = self.config;
+
+        if self.workers.len() == 1 || batch.len() == 1 {
+            let worker = &mut self.workers[0];
+            return batch
+                .iter()
+                .zip(analyses)
+                .map(|((index, function), analysis)| Ok((*index, worker.process_function(*index, function, Some(analysis), config)?)))
+                .collect();
+        }
+
+        let chunk_size = batch.len().div_ceil(self.workers.len());
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = batch
+                .chunks(chunk_size)
+                .zip(analyses.chunks(chunk_size))
+                .zip(self.workers.iter_mut())
+                .map(|((functions, analyses), worker)| {
+                    scope.spawn(move || {
+                        functions
+                            .iter()
+                            .zip(analyses)
+                            .map(|((index, function), analysis)| Ok((*index, worker.process_function(*index, function, Some(analysis), config)?)))
+                            .collect::<TranspilationResult<Vec<_>>>()
+                    })
+                })
+                .collect();
+
+            let mut translated = Vec::with_capacity(batch.len());
+            for handle in handles {
+                let chunk = handle.join().map_err(|_| TranspilationError::internal_error("Translation worker panicked"))??;
+                translated.extend(chunk);
+            }
+            Ok(translated)
+        })
+    }
+}
@@ -21,11 +21,12 @@ use super::{
         config::TranspilationConfig,
         error::{TranspilationError, TranspilationResult},
         processors::{ExportsProcessor, FunctionProcessor, GlobalsProcessor, MemoryProcessor, ModuleProcessor},
-        types::TranspiledModule,
+        types::{ElementSegment, TranspiledModule},
     },
     PipelineStage,
-    analyzer::AnalysisResult,
+    analyzer::{AnalysisResult, FunctionAnalysis, PerformanceProfile},
 };
+use crate::wasm::ast::{WasmInstruction, WasmModule};
 use dotvm_core::bytecode::BytecodeHeader;
 
 /// Translation stage that converts analyzed WASM to DotVM bytecode
@@ -53,39 +54,29 @@ impl Translator {
             exports_processor: ExportsProcessor::new(config)?,
         })
     }
-}
-
-impl PipelineStage for Translator {
-    type Input = AnalysisResult;
-    type Output = TranspiledModule;
 
-    fn execute(&mut self, input: Self::Input, config: &TranspilationConfig) -> TranspilationResult<Self::Output> {
+    /// Translate everything except function bodies: header, globals, memory layout, exports, imports and tables
+    pub(super) fn translate_module_shell(&mut self, module: &WasmModule, config: &TranspilationConfig) -> TranspilationResult<TranspiledModule> {
         // Create module header
         let header = BytecodeHeader::new(config.target_architecture);
         let mut transpiled_module = TranspiledModule::new(header);
 
         // Process the module structure
-        self.module_processor.process_module(&input.module, &mut transpiled_module, config)?;
-
-        // Process functions
-        let functions = self.function_processor.process_functions(&input.module.functions, &input.function_analyses, config)?;
-
-        for function in functions {
-            transpiled_module.add_function(function);
-        }
+        self.module_processor.process_module(module, &mut transpiled_module, config)?;
 
         // Process globals
-        let globals = self.globals_processor.process_globals(&input.module.globals, config)?;
+        let globals = self.globals_processor.process_globals(&module.globals, config)?;
         for global in globals {
             transpiled_module.add_global(global);
         }
 
-        // Process memory layout
-        let memory_layout = self.memory_processor.process_memory(&input.module.memories, config)?;
+        // Process memory layout, imported memories first as in the WASM index space
+        let memories: Vec<_> = module.memory_space().into_iter().cloned().collect();
+        let memory_layout = self.memory_processor.process_memory(&memories, config)?;
         transpiled_module.set_memory_layout(memory_layout);
 
         // Process exports and imports
-        let (exports, imports) = self.exports_processor.process_exports_imports(&input.module.exports, &input.module.imports, config)?;
+        let (exports, imports) = self.exports_processor.process_exports_imports(&module.exports, &module.imports, config)?;
 
         for export in exports {
             transpiled_module.add_export(export);
@@ -95,18 +86,51 @@ impl PipelineStage for Translator {
             transpiled_module.add_import(import);
         }
 
-        // Set module metadata from analysis
-        transpiled_module.metadata.set_complexity_score(input.function_analyses.iter().map(|f| f.complexity_score).sum());
+        // Keep table contents so indirect call targets stay visible to later passes
+        for element in &module.elements {
+            let offset = match element.offset.as_slice() {
+                [WasmInstruction::I32Const { value }] => u32::try_from(*value).ok(),
+                _ => None,
+            };
+            transpiled_module.add_element_segment(ElementSegment::new(element.table_index, offset, element.functions.clone()));
+        }
+        transpiled_module.start_function = module.start_function;
+
+        Ok(transpiled_module)
+    }
 
-        transpiled_module.metadata.estimated_size = input.performance_profile.estimated_memory_usage;
+    /// Copy complexity and performance characteristics from the analysis into module metadata
+    pub(super) fn apply_analysis_metadata(transpiled_module: &mut TranspiledModule, function_analyses: &[FunctionAnalysis], performance_profile: &PerformanceProfile) {
+        transpiled_module.metadata.set_complexity_score(function_analyses.iter().map(|f| f.complexity_score).sum());
 
-        if input.performance_profile.is_cpu_intensive {
+        transpiled_module.metadata.estimated_size = performance_profile.estimated_memory_usage;
+
+        if performance_profile.is_cpu_intensive {
             transpiled_module.metadata.add_optimization_hint("cpu_intensive".to_string());
         }
 
-        if input.performance_profile.is_memory_intensive {
+        if performance_profile.is_memory_intensive {
             transpiled_module.metadata.add_optimization_hint("memory_intensive".to_string());
         }
+    }
+}
+
+impl PipelineStage for Translator {
+    type Input = AnalysisResult;
+    type Output = TranspiledModule;
+
+    fn execute(&mut self, input: Self::Input, config: &TranspilationConfig) -> TranspilationResult<Self::Output> {
+        let mut transpiled_module = self.translate_module_shell(&input.module, config)?;
+
+        // Process functions
+        let functions = self.function_processor.process_functions(&input.module.functions, &input.function_analyses, config)?;
+
+        for function in functions {
+            transpiled_module.add_function(function);
+        }
+
+        // Set module metadata from analysis
+        Self::apply_analysis_metadata(&mut transpiled_module, &input.function_analyses, &input.performance_profile);
 
         Ok(transpiled_module)
     }
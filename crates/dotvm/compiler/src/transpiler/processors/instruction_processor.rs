@@ -46,7 +46,7 @@ impl InstructionProcessor {
 
             for mapped in mapped_instructions {
                 let transpiled = TranspiledInstruction::new(
-                    format!("{:?}", mapped.opcode),
+                    mapped.opcode.clone(),
                     mapped
                         .operands
                         .iter()
@@ -19,7 +19,7 @@
 use super::super::{
     config::TranspilationConfig,
     error::{TranspilationError, TranspilationResult},
-    types::MemoryLayout,
+    types::{MemoryLayout, MemoryRegion},
 };
 use crate::wasm::ast::WasmMemory;
 
@@ -32,13 +32,19 @@ impl MemoryProcessor {
         Ok(Self)
     }
 
-    /// Process memory layout
+    /// Process memory layout for all linear memories, given in index order
+    ///
+    /// The layout's page counts describe memory 0; each memory also gets its own region.
     pub fn process_memory(&mut self, wasm_memories: &[WasmMemory], _config: &TranspilationConfig) -> TranspilationResult<MemoryLayout> {
-        if let Some(memory) = wasm_memories.first() {
-            Ok(MemoryLayout::new(memory.initial_pages(), 65536).with_max_pages(memory.max_pages().unwrap_or(u32::MAX)))
-        } else {
-            Ok(MemoryLayout::default())
+        let Some(memory) = wasm_memories.first() else {
+            return Ok(MemoryLayout::default());
+        };
+
+        let mut layout = MemoryLayout::new(memory.initial_pages(), 65536).with_max_pages(memory.max_pages().unwrap_or(u32::MAX));
+        for (handle, memory) in wasm_memories.iter().enumerate() {
+            layout.add_region(MemoryRegion::new(handle as u32, memory.initial_pages(), memory.max_pages()).with_shared(memory.is_shared()));
         }
+        Ok(layout)
     }
 }
 
@@ -53,4 +59,19 @@ mod tests {
         let processor = MemoryProcessor::new(&config);
         assert!(processor.is_ok());
     }
+
+    #[test]
+    fn test_each_memory_gets_a_region() {
+        use crate::wasm::ast::WasmMemoryType;
+
+        let config = TranspilationConfig::default();
+        let mut processor = MemoryProcessor::new(&config).unwrap();
+        let memories = vec![WasmMemory::new(WasmMemoryType::new(1, Some(2), false)), WasmMemory::new(WasmMemoryType::new(4, None, true))];
+
+        let layout = processor.process_memory(&memories, &config).unwrap();
+        assert_eq!(layout.initial_pages, 1);
+        assert_eq!(layout.regions.len(), 2);
+        assert_eq!(layout.region(1), Some(&MemoryRegion::new(1, 4, None).with_shared(true)));
+        assert!(layout.region(2).is_none());
+    }
 }
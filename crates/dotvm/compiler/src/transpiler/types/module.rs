@@ -34,6 +34,10 @@ pub struct TranspiledModule {
     pub exports: Vec<ExportInfo>,
     /// Import information
     pub imports: Vec<ImportInfo>,
+    /// Table element segments referencing functions
+    pub element_segments: Vec<ElementSegment>,
+    /// Index of the function run when the module is instantiated
+    pub start_function: Option<u32>,
     /// Module metadata
     pub metadata: ModuleMetadata,
 }
@@ -48,6 +52,8 @@ impl TranspiledModule {
             memory_layout: MemoryLayout::default(),
             exports: Vec::new(),
             imports: Vec::new(),
+            element_segments: Vec::new(),
+            start_function: None,
             metadata: ModuleMetadata::default(),
         }
     }
@@ -72,6 +78,11 @@ impl TranspiledModule {
         self.imports.push(import);
     }
 
+    /// Add an element segment to the module
+    pub fn add_element_segment(&mut self, segment: ElementSegment) {
+        self.element_segments.push(segment);
+    }
+
     /// Set the memory layout
     pub fn set_memory_layout(&mut self, layout: MemoryLayout) {
         self.memory_layout = layout;
@@ -111,6 +122,35 @@ impl TranspiledModule {
     pub fn exported_functions(&self) -> Vec<&TranspiledFunction> {
         self.functions.iter().filter(|f| f.is_exported).collect()
     }
+
+    /// Number of imported functions, which precede `functions` in the function index space
+    pub fn imported_function_count(&self) -> u32 {
+        self.imports.iter().filter(|i| i.is_function()).count() as u32
+    }
+}
+
+/// Table element segment
+///
+/// Functions listed here may be called indirectly through their table slot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElementSegment {
+    /// Table the segment initializes
+    pub table_index: u32,
+    /// Constant offset into the table, if known at compile time
+    pub offset: Option<u32>,
+    /// Function indices placed in the table
+    pub function_indices: Vec<u32>,
+}
+
+impl ElementSegment {
+    /// Create a new element segment
+    pub fn new(table_index: u32, offset: Option<u32>, function_indices: Vec<u32>) -> Self {
+        Self {
+            table_index,
+            offset,
+            function_indices,
+        }
+    }
 }
 
 /// Module metadata for optimization and analysis
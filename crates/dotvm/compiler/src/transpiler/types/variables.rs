@@ -209,6 +209,8 @@ pub struct MemoryLayout {
     pub segments: Vec<MemorySegment>,
     /// Memory protection flags
     pub protection: MemoryProtection,
+    /// One region per linear memory, in WASM memory index order
+    pub regions: Vec<MemoryRegion>,
 }
 
 impl Default for MemoryLayout {
@@ -219,6 +221,7 @@ impl Default for MemoryLayout {
             page_size: 65536, // 64KB pages (WASM standard)
             segments: Vec::new(),
             protection: MemoryProtection::default(),
+            regions: Vec::new(),
         }
     }
 }
@@ -232,6 +235,7 @@ impl MemoryLayout {
             page_size,
             segments: Vec::new(),
             protection: MemoryProtection::default(),
+            regions: Vec::new(),
         }
     }
 
@@ -246,6 +250,16 @@ impl MemoryLayout {
         self.segments.push(segment);
     }
 
+    /// Add the region backing the next linear memory
+    pub fn add_region(&mut self, region: MemoryRegion) {
+        self.regions.push(region);
+    }
+
+    /// Get the region a memory instruction addresses by handle
+    pub fn region(&self, handle: u32) -> Option<&MemoryRegion> {
+        self.regions.iter().find(|region| region.handle == handle)
+    }
+
     /// Get the total initial memory size in bytes
     pub fn initial_size_bytes(&self) -> u64 {
         self.initial_pages as u64 * self.page_size as u64
@@ -262,6 +276,40 @@ impl MemoryLayout {
     }
 }
 
+/// Region backing one WASM linear memory
+///
+/// Every linear memory is allocated as its own memory handle at load time, so memory
+/// instructions address a region by handle plus offset and never by a shared base address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryRegion {
+    /// Handle memory instructions use for this region (the WASM memory index)
+    pub handle: u32,
+    /// Initial number of pages
+    pub initial_pages: u32,
+    /// Maximum number of pages (if limited)
+    pub maximum_pages: Option<u32>,
+    /// Whether the memory is shared between threads
+    pub shared: bool,
+}
+
+impl MemoryRegion {
+    /// Create a region for memory `handle`
+    pub fn new(handle: u32, initial_pages: u32, maximum_pages: Option<u32>) -> Self {
+        Self {
+            handle,
+            initial_pages,
+            maximum_pages,
+            shared: false,
+        }
+    }
+
+    /// Mark the region as shared
+    pub fn with_shared(mut self, shared: bool) -> Self {
+        self.shared = shared;
+        self
+    }
+}
+
 /// Memory segment information
 #[derive(Debug, Clone)]
 pub struct MemorySegment {
@@ -83,12 +83,12 @@ pub enum WasmInstruction {
     I64Store8 { memarg: MemArg },
     I64Store16 { memarg: MemArg },
     I64Store32 { memarg: MemArg },
-    MemorySize,
-    MemoryGrow,
-    MemoryInit { data_index: u32 },
+    MemorySize { memory: u32 },
+    MemoryGrow { memory: u32 },
+    MemoryInit { data_index: u32, memory: u32 },
     DataDrop { data_index: u32 },
-    MemoryCopy,
-    MemoryFill,
+    MemoryCopy { dst_memory: u32, src_memory: u32 },
+    MemoryFill { memory: u32 },
 
     // Numeric instructions - Constants
     I32Const { value: i32 },
@@ -497,8 +497,12 @@ impl WasmInstruction {
             Self::I64Store { .. } => "i64.store",
             Self::F32Store { .. } => "f32.store",
             Self::F64Store { .. } => "f64.store",
-            Self::MemorySize => "memory.size",
-            Self::MemoryGrow => "memory.grow",
+            Self::MemorySize { .. } => "memory.size",
+            Self::MemoryGrow { .. } => "memory.grow",
+            Self::MemoryInit { .. } => "memory.init",
+            Self::DataDrop { .. } => "data.drop",
+            Self::MemoryCopy { .. } => "memory.copy",
+            Self::MemoryFill { .. } => "memory.fill",
             Self::I32Const { .. } => "i32.const",
             Self::I64Const { .. } => "i64.const",
             Self::F32Const { .. } => "f32.const",
@@ -572,17 +576,51 @@ impl WasmInstruction {
                 | Self::I64Store8 { .. }
                 | Self::I64Store16 { .. }
                 | Self::I64Store32 { .. }
-                | Self::MemorySize
-                | Self::MemoryGrow
+                | Self::MemorySize { .. }
+                | Self::MemoryGrow { .. }
                 | Self::MemoryInit { .. }
                 | Self::DataDrop { .. }
-                | Self::MemoryCopy
-                | Self::MemoryFill
+                | Self::MemoryCopy { .. }
+                | Self::MemoryFill { .. }
                 | Self::V128Load { .. }
                 | Self::V128Store { .. }
         )
     }
 
+    /// Linear memories this instruction reads or writes, in operand order
+    pub fn memory_indices(&self) -> Vec<u32> {
+        match self {
+            Self::I32Load { memarg }
+            | Self::I64Load { memarg }
+            | Self::F32Load { memarg }
+            | Self::F64Load { memarg }
+            | Self::I32Load8S { memarg }
+            | Self::I32Load8U { memarg }
+            | Self::I32Load16S { memarg }
+            | Self::I32Load16U { memarg }
+            | Self::I64Load8S { memarg }
+            | Self::I64Load8U { memarg }
+            | Self::I64Load16S { memarg }
+            | Self::I64Load16U { memarg }
+            | Self::I64Load32S { memarg }
+            | Self::I64Load32U { memarg }
+            | Self::I32Store { memarg }
+            | Self::I64Store { memarg }
+            | Self::F32Store { memarg }
+            | Self::F64Store { memarg }
+            | Self::I32Store8 { memarg }
+            | Self::I32Store16 { memarg }
+            | Self::I64Store8 { memarg }
+            | Self::I64Store16 { memarg }
+            | Self::I64Store32 { memarg }
+            | Self::V128Load { memarg }
+            | Self::V128Store { memarg } => vec![memarg.memory],
+            Self::MemorySize { memory } | Self::MemoryGrow { memory } | Self::MemoryInit { memory, .. } | Self::MemoryFill { memory } => vec![*memory],
+            Self::MemoryCopy { dst_memory, src_memory } => vec![*dst_memory, *src_memory],
+            _ => Vec::new(),
+        }
+    }
+
     /// Check if this instruction is a constant
     pub fn is_constant(&self) -> bool {
         matches!(
@@ -758,5 +796,22 @@ mod tests {
         assert_eq!(WasmInstruction::F64Mul.name(), "f64.mul");
         assert_eq!(WasmInstruction::LocalGet { local_index: 0 }.name(), "local.get");
         assert_eq!(WasmInstruction::Call { function_index: 0 }.name(), "call");
+        assert_eq!(WasmInstruction::MemoryCopy { dst_memory: 0, src_memory: 1 }.name(), "memory.copy");
+        assert_eq!(WasmInstruction::DataDrop { data_index: 0 }.name(), "data.drop");
+    }
+
+    #[test]
+    fn test_memory_indices() {
+        let copy = WasmInstruction::MemoryCopy { dst_memory: 1, src_memory: 0 };
+        assert!(copy.accesses_memory());
+        assert_eq!(copy.memory_indices(), vec![1, 0]);
+
+        let load = WasmInstruction::I64Load {
+            memarg: MemArg::new(8, 3).with_memory(2),
+        };
+        assert_eq!(load.memory_indices(), vec![2]);
+
+        assert!(WasmInstruction::DataDrop { data_index: 0 }.memory_indices().is_empty());
+        assert!(WasmInstruction::I32Add.memory_indices().is_empty());
     }
 }
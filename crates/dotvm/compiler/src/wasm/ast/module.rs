@@ -110,6 +110,18 @@ impl WasmModule {
         self.imports.iter().filter(|imp| matches!(imp.kind, WasmImportKind::Memory(_))).count()
     }
 
+    /// Get all memories in index order: imported memories first, then defined ones
+    pub fn memory_space(&self) -> Vec<&WasmMemory> {
+        self.imports
+            .iter()
+            .filter_map(|imp| match &imp.kind {
+                WasmImportKind::Memory(memory) => Some(memory),
+                _ => None,
+            })
+            .chain(self.memories.iter())
+            .collect()
+    }
+
     /// Find an export by name
     pub fn find_export(&self, name: &str) -> Option<&WasmExport> {
         self.exports.iter().find(|exp| exp.name == name)
@@ -170,6 +182,21 @@ impl WasmModule {
             }
         }
 
+        // Check memory indices used by active data segments and instructions
+        let memory_count = self.total_memory_count();
+        for (i, segment) in self.data_segments.iter().enumerate() {
+            if !segment.offset.is_empty() && segment.memory_index as usize >= memory_count {
+                return Err(format!("Data segment {} references invalid memory index {}", i, segment.memory_index));
+            }
+        }
+        for (i, function) in self.functions.iter().enumerate() {
+            for instruction in &function.body {
+                if let Some(memory) = instruction.memory_indices().into_iter().find(|&memory| memory as usize >= memory_count) {
+                    return Err(format!("Function {} uses {} on invalid memory index {}", i, instruction.name(), memory));
+                }
+            }
+        }
+
         // Check start function
         if let Some(start_func) = self.start_function {
             if start_func as usize >= self.total_function_count() {
@@ -552,4 +579,33 @@ mod tests {
         assert_eq!(segment.size(), 4);
         assert!(!segment.is_empty());
     }
+
+    #[test]
+    fn test_memory_space_and_indices() {
+        let mut module = WasmModule::new();
+        module.memories.push(WasmMemory::new(WasmMemoryType::new(2, None, false)));
+        module.imports.push(WasmImport::new(
+            "env".to_string(),
+            "shared".to_string(),
+            WasmImportKind::Memory(WasmMemory::new(WasmMemoryType::new(1, Some(4), false))),
+        ));
+        module.types.push(WasmFunctionType::empty());
+        module.function_types.push(0);
+        module.functions.push(WasmFunction::new(
+            WasmFunctionType::empty(),
+            vec![],
+            vec![WasmInstruction::MemoryCopy { dst_memory: 1, src_memory: 0 }, WasmInstruction::End],
+        ));
+
+        // The imported memory comes first in the index space
+        let memories = module.memory_space();
+        assert_eq!(memories.len(), 2);
+        assert_eq!(memories[0].max_pages(), Some(4));
+        assert_eq!(memories[1].initial_pages(), 2);
+        assert!(module.validate().is_ok());
+
+        module.functions[0].body[0] = WasmInstruction::MemoryFill { memory: 2 };
+        let error = module.validate().unwrap_err();
+        assert!(error.contains("memory.fill on invalid memory index 2"), "{error}");
+    }
 }
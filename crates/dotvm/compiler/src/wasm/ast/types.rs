@@ -335,12 +335,21 @@ pub struct MemArg {
     pub offset: u64,
     /// Alignment (power of 2)
     pub align: u32,
+    /// Index of the linear memory being accessed (non-zero only with multi-memory)
+    #[serde(default)]
+    pub memory: u32,
 }
 
 impl MemArg {
-    /// Create a new memory argument
+    /// Create a new memory argument for memory 0
     pub fn new(offset: u64, align: u32) -> Self {
-        Self { offset, align }
+        Self { offset, align, memory: 0 }
+    }
+
+    /// Access another linear memory
+    pub fn with_memory(mut self, memory: u32) -> Self {
+        self.memory = memory;
+        self
     }
 
     /// Create a memory argument with zero offset
@@ -61,6 +61,9 @@ pub enum WasmError {
     #[error("Unsupported WASM feature: {feature}")]
     UnsupportedFeature { feature: String },
 
+    #[error("Unsupported WASM feature {feature} in function {function} at offset {offset:#x}: {instruction}")]
+    UnsupportedFeatureAt { feature: String, instruction: String, function: u32, offset: usize },
+
     #[error("Feature {feature} requires {requirement} but it is not enabled")]
     FeatureRequirementNotMet { feature: String, requirement: String },
 
@@ -195,7 +198,7 @@ impl WasmError {
             | Self::InvalidGlobalIndex { .. }
             | Self::InvalidMemoryIndex { .. }
             | Self::InvalidTableIndex { .. } => ErrorCategory::Type,
-            Self::UnsupportedFeature { .. } | Self::FeatureRequirementNotMet { .. } | Self::UnsupportedProposal { .. } => ErrorCategory::Feature,
+            Self::UnsupportedFeature { .. } | Self::UnsupportedFeatureAt { .. } | Self::FeatureRequirementNotMet { .. } | Self::UnsupportedProposal { .. } => ErrorCategory::Feature,
             Self::ValidationFailed(_) | Self::InvalidFunctionBody { .. } | Self::InvalidInstruction { .. } | Self::StackUnderflow { .. } | Self::StackOverflow { .. } => ErrorCategory::Validation,
             Self::UnsupportedInstruction { .. } | Self::InvalidOperand { .. } | Self::IncompatibleArchitecture { .. } | Self::MappingFailed { .. } => ErrorCategory::Mapping,
             Self::InvalidModuleStructure(_) | Self::MissingRequiredSection { .. } | Self::DuplicateSection { .. } | Self::InvalidSectionOrder { .. } => ErrorCategory::Module,
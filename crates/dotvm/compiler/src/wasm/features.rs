@@ -33,7 +33,7 @@ impl FeatureDetector {
             WasmInstruction::V128Load { .. } | WasmInstruction::V128Store { .. } | WasmInstruction::V128Const { .. } => {
                 features.push("simd".to_string());
             }
-            WasmInstruction::MemoryCopy | WasmInstruction::MemoryFill | WasmInstruction::MemoryInit { .. } | WasmInstruction::DataDrop { .. } => {
+            WasmInstruction::MemoryCopy { .. } | WasmInstruction::MemoryFill { .. } | WasmInstruction::MemoryInit { .. } | WasmInstruction::DataDrop { .. } => {
                 features.push("bulk_memory".to_string());
             }
             WasmInstruction::RefNull { .. } | WasmInstruction::RefIsNull | WasmInstruction::RefFunc { .. } => {
@@ -45,6 +45,10 @@ impl FeatureDetector {
             _ => {}
         }
 
+        if instruction.memory_indices().iter().any(|&memory| memory != 0) {
+            features.push("multi_memory".to_string());
+        }
+
         features
     }
 
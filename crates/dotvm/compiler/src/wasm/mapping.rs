@@ -24,6 +24,7 @@ use super::{
     error::{WasmError, WasmResult},
 };
 use dotvm_core::bytecode::VmArchitecture;
+use dotvm_core::opcode::wide_arithmetic_opcodes::WideArithmeticOpcode;
 
 /// New opcode mapper with improved architecture
 pub struct OpcodeMapper {
@@ -39,6 +40,13 @@ impl OpcodeMapper {
 
     /// Map a WASM instruction to DotVM opcodes
     pub fn map_instruction(&self, instruction: &WasmInstruction) -> WasmResult<Vec<MappedInstruction>> {
+        if let Some(wide_opcode) = self.wide_opcode(instruction) {
+            return Ok(vec![MappedInstruction {
+                opcode: wide_opcode.to_mnemonic().to_string(),
+                operands: vec![],
+            }]);
+        }
+
         // Placeholder implementation - would delegate to the old mapper for now
         // This allows for gradual migration
         // Default mapping: use the instruction name and operands where applicable
@@ -93,11 +101,25 @@ impl OpcodeMapper {
                 vec![]
             }
 
+            // Memory accesses lead with the memory index, which selects that memory's region
+            WasmInstruction::I32Load { memarg } | WasmInstruction::I64Load { memarg } | WasmInstruction::I32Store { memarg } | WasmInstruction::I64Store { memarg } => {
+                vec![memarg.memory as u64, memarg.offset]
+            }
+            WasmInstruction::MemorySize { memory } | WasmInstruction::MemoryGrow { memory } => vec![*memory as u64],
+
+            // Bulk memory operations map to single range intrinsics instead of per-byte loops
+            WasmInstruction::MemoryCopy { dst_memory, src_memory } => vec![*dst_memory as u64, *src_memory as u64],
+            WasmInstruction::MemoryFill { memory } => vec![*memory as u64],
+            WasmInstruction::MemoryInit { data_index, memory } => vec![*memory as u64, *data_index as u64],
+            WasmInstruction::DataDrop { data_index } => vec![*data_index as u64],
+
             // Simple stack operations
             WasmInstruction::Drop | WasmInstruction::Select => vec![],
 
             // Control flow instructions
             WasmInstruction::Nop | WasmInstruction::End => vec![],
+            // Direct calls name the callee by its index in the module's function space
+            WasmInstruction::Call { function_index } => vec![*function_index as u64],
 
             // Unsupported or complex features
             _ => return Err(WasmError::unsupported_feature(format!("Instruction: {:?}", instruction))),
@@ -106,6 +128,30 @@ impl OpcodeMapper {
         Ok(vec![MappedInstruction { opcode, operands: mapped }])
     }
 
+    /// Wide variant of a 64-bit integer instruction when targeting a 128-bit or wider architecture
+    ///
+    /// Integers there are architecture words, so `i64` arithmetic runs at the word
+    /// width instead of being truncated to 64 bits.
+    fn wide_opcode(&self, instruction: &WasmInstruction) -> Option<WideArithmeticOpcode> {
+        if self.target_architecture.bit_width() <= 64 {
+            return None;
+        }
+
+        match instruction {
+            WasmInstruction::I64Add => Some(WideArithmeticOpcode::Add),
+            WasmInstruction::I64Sub => Some(WideArithmeticOpcode::Subtract),
+            WasmInstruction::I64Mul => Some(WideArithmeticOpcode::Multiply),
+            WasmInstruction::I64DivU => Some(WideArithmeticOpcode::DivideUnsigned),
+            WasmInstruction::I64DivS => Some(WideArithmeticOpcode::DivideSigned),
+            WasmInstruction::I64RemU => Some(WideArithmeticOpcode::ModulusUnsigned),
+            WasmInstruction::I64RemS => Some(WideArithmeticOpcode::ModulusSigned),
+            WasmInstruction::I64Shl => Some(WideArithmeticOpcode::ShiftLeft),
+            WasmInstruction::I64ShrU => Some(WideArithmeticOpcode::ShiftRightUnsigned),
+            WasmInstruction::I64ShrS => Some(WideArithmeticOpcode::ShiftRightSigned),
+            _ => None,
+        }
+    }
+
     /// Get the required architecture for an instruction
     pub fn required_architecture(instruction: &WasmInstruction) -> VmArchitecture {
         match instruction {
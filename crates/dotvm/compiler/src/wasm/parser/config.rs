@@ -40,6 +40,8 @@ pub struct ParserConfig {
     pub allow_multi_value: bool,
     /// Whether to allow bulk memory operations
     pub allow_bulk_memory: bool,
+    /// Whether to allow more than one linear memory
+    pub allow_multi_memory: bool,
     /// Whether to allow SIMD instructions
     pub allow_simd: bool,
     /// Whether to allow reference types
@@ -49,8 +51,15 @@ pub struct ParserConfig {
 impl Default for ParserConfig {
     fn default() -> Self {
         Self {
-            features: WasmFeatures::default(),
-            limits: SectionLimits::default(),
+            features: WasmFeatures {
+                multi_memory: true,
+                ..WasmFeatures::default()
+            },
+            // Multi-memory is allowed, so the single-memory limit does not apply
+            limits: SectionLimits {
+                max_memories: None,
+                ..SectionLimits::default()
+            },
             validate_structure: true,
             preserve_custom_sections: true,
             parse_debug_info: true,
@@ -58,6 +67,7 @@ impl Default for ParserConfig {
             max_nesting_depth: 1024,
             allow_multi_value: true,
             allow_bulk_memory: true,
+            allow_multi_memory: true,
             allow_simd: true,
             allow_reference_types: true,
         }
@@ -82,6 +92,7 @@ impl ParserConfig {
             max_nesting_depth: 100,
             allow_multi_value: false,
             allow_bulk_memory: false,
+            allow_multi_memory: false,
             allow_simd: false,
             allow_reference_types: false,
         }
@@ -99,6 +110,7 @@ impl ParserConfig {
             max_nesting_depth: 10000,
             allow_multi_value: true,
             allow_bulk_memory: true,
+            allow_multi_memory: true,
             allow_simd: true,
             allow_reference_types: true,
         }
@@ -164,6 +176,16 @@ impl ParserConfig {
         self
     }
 
+    /// Enable or disable multiple linear memories, lifting the memory count limit when enabled
+    pub fn with_multi_memory(mut self, allow: bool) -> Self {
+        self.allow_multi_memory = allow;
+        if allow {
+            self.features.multi_memory = true;
+            self.limits.max_memories = None;
+        }
+        self
+    }
+
     /// Enable or disable SIMD instructions
     pub fn with_simd(mut self, allow: bool) -> Self {
         self.allow_simd = allow;
@@ -200,6 +222,7 @@ impl ParserConfig {
         match feature {
             "multi_value" => self.allow_multi_value && self.features.multi_value,
             "bulk_memory" => self.allow_bulk_memory && self.features.bulk_memory,
+            "multi_memory" => self.allow_multi_memory && self.features.multi_memory,
             "simd" => self.allow_simd && self.features.simd,
             "reference_types" => self.allow_reference_types && self.features.reference_types,
             "threads" => self.features.threads,
@@ -223,6 +246,9 @@ impl ParserConfig {
         if self.is_feature_enabled("bulk_memory") {
             features.push("bulk_memory".to_string());
         }
+        if self.is_feature_enabled("multi_memory") {
+            features.push("multi_memory".to_string());
+        }
         if self.is_feature_enabled("simd") {
             features.push("simd".to_string());
         }
@@ -336,6 +362,16 @@ impl ParserConfigBuilder {
         self
     }
 
+    /// Enable multi-memory
+    pub fn multi_memory(mut self, enable: bool) -> Self {
+        self.config.allow_multi_memory = enable;
+        if enable {
+            self.config.features.multi_memory = true;
+            self.config.limits.max_memories = None;
+        }
+        self
+    }
+
     /// Enable SIMD
     pub fn simd(mut self, enable: bool) -> Self {
         self.config.allow_simd = enable;
@@ -401,9 +437,18 @@ mod tests {
         let config = ParserConfig::default();
         assert!(config.is_feature_enabled("multi_value"));
         assert!(config.is_feature_enabled("bulk_memory"));
+        assert!(config.is_feature_enabled("multi_memory"));
 
         let strict_config = ParserConfig::strict();
         assert!(!strict_config.is_feature_enabled("simd"));
+        assert!(!strict_config.is_feature_enabled("multi_memory"));
+    }
+
+    #[test]
+    fn test_multi_memory_lifts_the_memory_limit() {
+        assert_eq!(ParserConfig::default().limits.max_memories, None);
+        assert_eq!(ParserConfig::strict().with_multi_memory(true).limits.max_memories, None);
+        assert_eq!(ParserConfig::strict().with_multi_memory(false).limits.max_memories, Some(1));
     }
 
     #[test]
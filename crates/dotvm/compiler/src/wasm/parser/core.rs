@@ -21,7 +21,8 @@ use super::super::{
     error::{WasmError, WasmResult},
 };
 use super::{ParserConfig, ParserContext};
-use wasmparser::{Parser, Payload, WasmFeatures};
+use std::io::Read;
+use wasmparser::{Chunk, Parser, Payload, WasmFeatures};
 
 /// Main WebAssembly parser
 pub struct WasmParser {
@@ -84,6 +85,90 @@ impl WasmParser {
         Ok(module)
     }
 
+    /// Parse a WASM binary from a reader without holding the whole input in memory
+    ///
+    /// Input is pulled `chunk_size` bytes at a time. Every function body is decoded as soon as
+    /// it is available and handed to `on_function` together with its index among the module's
+    /// defined functions; bodies are not retained, so the returned module has an empty
+    /// `functions` list.
+    pub fn parse_streaming<R, F, E>(&mut self, mut reader: R, chunk_size: usize, mut on_function: F) -> Result<WasmModule, E>
+    where
+        R: Read,
+        F: FnMut(u32, WasmFunction) -> Result<(), E>,
+        E: From<WasmError>,
+    {
+        self.context.reset();
+        self.context.start_parsing();
+        self.config.validate()?;
+
+        let mut parser = Parser::new(0);
+        let mut module = WasmModule::new();
+        let mut buffer = Vec::new();
+        let mut eof = false;
+
+        let mut type_section: Vec<WasmFunctionType> = Vec::new();
+        let mut function_section: Vec<u32> = Vec::new();
+        let mut defined_functions = 0usize;
+
+        loop {
+            let (payload, consumed) = match parser.parse(&buffer, eof).map_err(WasmError::ParserError)? {
+                Chunk::NeedMoreData(hint) => {
+                    if eof {
+                        return Err(WasmError::InvalidBinary("Unexpected end of WASM input".to_string()).into());
+                    }
+
+                    let start = buffer.len();
+                    buffer.resize(start + (hint as usize).max(chunk_size.max(1)), 0);
+                    let read = reader
+                        .read(&mut buffer[start..])
+                        .map_err(|e| WasmError::InvalidBinary(format!("Failed to read WASM input: {}", e)))?;
+                    buffer.truncate(start + read);
+                    self.context.metrics.bytes_parsed += read;
+                    eof = read == 0;
+                    continue;
+                }
+                Chunk::Parsed { consumed, payload } => (payload, consumed),
+            };
+
+            match payload {
+                Payload::End(_) => break,
+                Payload::CodeSectionEntry(body) => {
+                    let section_start = std::time::Instant::now();
+                    let function = self.parse_function_body(&body, defined_functions, module.import_function_count(), &function_section, &type_section)?;
+                    self.context.record_section_time(WasmSectionType::Code, section_start.elapsed());
+                    on_function(defined_functions as u32, function)?;
+                    defined_functions += 1;
+                }
+                payload => self.parse_payload(payload, &mut module, &mut type_section, &mut function_section, &mut Vec::new())?,
+            }
+
+            buffer.drain(..consumed);
+        }
+
+        if defined_functions != function_section.len() {
+            return Err(WasmError::InvalidModuleStructure(format!(
+                "Function section declares {} functions but the code section contains {}",
+                function_section.len(),
+                defined_functions
+            ))
+            .into());
+        }
+
+        self.finalize_functions(&mut module, type_section, function_section, Vec::new())?;
+
+        // Exports and the start function are checked by the caller, which knows how many functions were streamed
+        if self.config.validate_structure {
+            for (index, &type_index) in module.function_types.iter().enumerate() {
+                if type_index as usize >= module.types.len() {
+                    return Err(WasmError::validation_failed(format!("Function {} references invalid type index {}", index, type_index)).into());
+                }
+            }
+        }
+
+        self.context.finish_parsing();
+        Ok(module)
+    }
+
     /// Parse a single payload
     fn parse_payload(
         &mut self,
@@ -154,7 +239,7 @@ impl WasmParser {
 
             Payload::CodeSectionEntry(body) => {
                 let section_start = std::time::Instant::now();
-                let function = self.parse_function_body(&body, function_section, type_section)?;
+                let function = self.parse_function_body(&body, code_section.len(), module.import_function_count(), function_section, type_section)?;
                 code_section.push(function);
                 self.context.record_section_time(WasmSectionType::Code, section_start.elapsed());
             }
@@ -210,8 +295,8 @@ impl WasmParser {
             module.imports.push(self.convert_import(&import)?);
         }
 
-        self.config.limits.validate_count(WasmSectionType::Import, module.imports.len())?;
-        Ok(())
+        self.check_memory_count(module)?;
+        self.config.limits.validate_count(WasmSectionType::Import, module.imports.len())
     }
 
     /// Parse function section
@@ -251,7 +336,15 @@ impl WasmParser {
                 .push(WasmMemory::new(WasmMemoryType::new(memory.initial as u32, memory.maximum.map(|m| m as u32), memory.shared)));
         }
 
-        self.config.limits.validate_count(WasmSectionType::Memory, module.memories.len())?;
+        self.check_memory_count(module)?;
+        self.config.limits.validate_count(WasmSectionType::Memory, module.memories.len())
+    }
+
+    /// Reject a second linear memory, imported or defined, unless multi-memory is enabled
+    fn check_memory_count(&self, module: &WasmModule) -> WasmResult<()> {
+        if module.total_memory_count() > 1 && !self.config.allow_multi_memory {
+            return Err(WasmError::unsupported_feature(format!("multi_memory ({} linear memories declared)", module.total_memory_count())));
+        }
         Ok(())
     }
 
@@ -325,8 +418,17 @@ impl WasmParser {
     }
 
     /// Parse function body
-    fn parse_function_body(&mut self, body: &wasmparser::FunctionBody, function_section: &[u32], type_section: &[WasmFunctionType]) -> WasmResult<WasmFunction> {
-        let function_index = function_section.len().saturating_sub(1);
+    ///
+    /// `function_index` counts defined functions only; errors report the index in the
+    /// module's function space, which starts after `imported_functions`.
+    fn parse_function_body(
+        &mut self,
+        body: &wasmparser::FunctionBody,
+        function_index: usize,
+        imported_functions: usize,
+        function_section: &[u32],
+        type_section: &[WasmFunctionType],
+    ) -> WasmResult<WasmFunction> {
         let type_index = function_section.get(function_index).ok_or_else(|| WasmError::InvalidFunctionIndex { index: function_index as u32 })?;
 
         let func_type = type_section.get(*type_index as usize).ok_or_else(|| WasmError::InvalidTypeIndex { index: *type_index })?.clone();
@@ -344,11 +446,20 @@ impl WasmParser {
             }
         }
 
-        // Parse instructions
+        // Parse instructions, locating any unsupported feature by function and byte offset
         let operators_reader = body.get_operators_reader().map_err(WasmError::ParserError)?;
-        for op in operators_reader {
-            let op = op.map_err(WasmError::ParserError)?;
-            instructions.push(self.convert_operator(&op)?);
+        for op in operators_reader.into_iter_with_offsets() {
+            let (op, offset) = op.map_err(WasmError::ParserError)?;
+            let instruction = self.convert_operator(&op).map_err(|e| match e {
+                WasmError::UnsupportedFeature { feature } => WasmError::UnsupportedFeatureAt {
+                    feature,
+                    instruction: format!("{:?}", op),
+                    function: (imported_functions + function_index) as u32,
+                    offset,
+                },
+                e => e,
+            })?;
+            instructions.push(instruction);
         }
 
         Ok(WasmFunction::new(func_type, locals, instructions))
@@ -361,7 +472,11 @@ impl WasmParser {
 
         for op in reader {
             let op = op.map_err(WasmError::ParserError)?;
-            instructions.push(self.convert_operator(&op)?);
+            let instruction = self.convert_operator(&op).map_err(|e| match e {
+                WasmError::UnsupportedFeature { feature } => WasmError::unsupported_feature(format!("{} ({:?} in a constant expression)", feature, op)),
+                e => e,
+            })?;
+            instructions.push(instruction);
         }
 
         Ok(instructions)
@@ -486,18 +601,36 @@ impl WasmParser {
             wasmparser::Operator::I64Mul => Ok(WasmInstruction::I64Mul),
 
             // Memory
-            wasmparser::Operator::I32Load { memarg } => Ok(WasmInstruction::I32Load {
-                memarg: MemArg::new(memarg.offset, memarg.align as u32),
-            }),
-            wasmparser::Operator::I64Load { memarg } => Ok(WasmInstruction::I64Load {
-                memarg: MemArg::new(memarg.offset, memarg.align as u32),
-            }),
-            wasmparser::Operator::I32Store { memarg } => Ok(WasmInstruction::I32Store {
-                memarg: MemArg::new(memarg.offset, memarg.align as u32),
-            }),
-            wasmparser::Operator::I64Store { memarg } => Ok(WasmInstruction::I64Store {
-                memarg: MemArg::new(memarg.offset, memarg.align as u32),
-            }),
+            wasmparser::Operator::I32Load { memarg } => Ok(WasmInstruction::I32Load { memarg: self.convert_memarg(memarg)? }),
+            wasmparser::Operator::I64Load { memarg } => Ok(WasmInstruction::I64Load { memarg: self.convert_memarg(memarg)? }),
+            wasmparser::Operator::I32Store { memarg } => Ok(WasmInstruction::I32Store { memarg: self.convert_memarg(memarg)? }),
+            wasmparser::Operator::I64Store { memarg } => Ok(WasmInstruction::I64Store { memarg: self.convert_memarg(memarg)? }),
+            wasmparser::Operator::MemorySize { mem, .. } => Ok(WasmInstruction::MemorySize { memory: self.memory_index(*mem)? }),
+            wasmparser::Operator::MemoryGrow { mem, .. } => Ok(WasmInstruction::MemoryGrow { memory: self.memory_index(*mem)? }),
+
+            // Bulk memory
+            wasmparser::Operator::MemoryCopy { dst_mem, src_mem } => {
+                self.require_bulk_memory()?;
+                Ok(WasmInstruction::MemoryCopy {
+                    dst_memory: self.memory_index(*dst_mem)?,
+                    src_memory: self.memory_index(*src_mem)?,
+                })
+            }
+            wasmparser::Operator::MemoryFill { mem } => {
+                self.require_bulk_memory()?;
+                Ok(WasmInstruction::MemoryFill { memory: self.memory_index(*mem)? })
+            }
+            wasmparser::Operator::MemoryInit { data_index, mem } => {
+                self.require_bulk_memory()?;
+                Ok(WasmInstruction::MemoryInit {
+                    data_index: *data_index,
+                    memory: self.memory_index(*mem)?,
+                })
+            }
+            wasmparser::Operator::DataDrop { data_index } => {
+                self.require_bulk_memory()?;
+                Ok(WasmInstruction::DataDrop { data_index: *data_index })
+            }
 
             // Variables
             wasmparser::Operator::LocalGet { local_index } => Ok(WasmInstruction::LocalGet { local_index: *local_index }),
@@ -506,8 +639,29 @@ impl WasmParser {
             wasmparser::Operator::GlobalSet { global_index } => Ok(WasmInstruction::GlobalSet { global_index: *global_index }),
 
             // Add more operators as needed...
-            _ => Err(WasmError::unsupported_feature(format!("Operator: {:?}", op))),
+            _ => Err(WasmError::unsupported_feature(operator_proposal(op))),
+        }
+    }
+
+    /// Convert a memory argument, checking the memory it addresses
+    fn convert_memarg(&self, memarg: &wasmparser::MemArg) -> WasmResult<MemArg> {
+        Ok(MemArg::new(memarg.offset, memarg.align as u32).with_memory(self.memory_index(memarg.memory)?))
+    }
+
+    /// Check that a memory other than memory 0 is only addressed with multi-memory enabled
+    fn memory_index(&self, memory: u32) -> WasmResult<u32> {
+        if memory != 0 && !self.config.allow_multi_memory {
+            return Err(WasmError::unsupported_feature("multi_memory"));
+        }
+        Ok(memory)
+    }
+
+    /// Check that bulk memory operations are enabled
+    fn require_bulk_memory(&self) -> WasmResult<()> {
+        if !self.config.allow_bulk_memory {
+            return Err(WasmError::unsupported_feature("bulk_memory"));
         }
+        Ok(())
     }
 
     /// Convert wasmparser block type to our type
@@ -554,6 +708,22 @@ impl WasmParser {
     }
 }
 
+/// Defines `operator_proposal`, naming the proposal an operator comes from as wasmparser does
+/// (`mvp` for the core instruction set, otherwise e.g. `simd`, `threads` or `exceptions`)
+macro_rules! define_operator_proposal {
+    ($( @$proposal:ident $op:ident $({ $($arg:ident: $argty:ty),* })? => $visit:ident)*) => {
+        fn operator_proposal(op: &wasmparser::Operator) -> &'static str {
+            #[allow(unreachable_patterns)]
+            match op {
+                $(wasmparser::Operator::$op { .. } => stringify!($proposal),)*
+                _ => "unknown",
+            }
+        }
+    };
+}
+
+wasmparser::for_each_operator!(define_operator_proposal);
+
 impl Default for WasmParser {
     fn default() -> Self {
         Self::new()
@@ -605,4 +775,40 @@ mod tests {
         assert_eq!(module.types.len(), 0);
         assert_eq!(module.functions.len(), 0);
     }
+
+    /// Two `() -> i32` functions computing `1 + 2`
+    const TWO_FUNCTIONS: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+        0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type section
+        0x03, 0x03, 0x02, 0x00, 0x00, // function section
+        0x0a, 0x11, 0x02, // code section
+        0x07, 0x00, 0x41, 0x01, 0x41, 0x02, 0x6a, 0x0b, // function 0
+        0x07, 0x00, 0x41, 0x01, 0x41, 0x02, 0x6a, 0x0b, // function 1
+    ];
+
+    #[test]
+    fn test_streaming_parse_matches_batch_parse() {
+        let batch = WasmParser::new().parse(TWO_FUNCTIONS).unwrap();
+
+        let mut streamed_functions = Vec::new();
+        let module = WasmParser::new()
+            .parse_streaming(std::io::Cursor::new(TWO_FUNCTIONS), 3, |index, function| {
+                streamed_functions.push((index, function));
+                Ok::<(), WasmError>(())
+            })
+            .unwrap();
+
+        assert!(module.functions.is_empty());
+        assert_eq!(module.function_types, batch.function_types);
+        assert_eq!(streamed_functions.len(), 2);
+        assert_eq!(streamed_functions[1].0, 1);
+        assert_eq!(streamed_functions[1].1.body, batch.functions[1].body);
+    }
+
+    #[test]
+    fn test_streaming_parse_truncated_input() {
+        let truncated = &TWO_FUNCTIONS[..TWO_FUNCTIONS.len() - 4];
+        let result = WasmParser::new().parse_streaming(std::io::Cursor::new(truncated), 8, |_, _| Ok::<(), WasmError>(()));
+        assert!(result.is_err());
+    }
 }
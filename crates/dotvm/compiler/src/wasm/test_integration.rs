@@ -209,15 +209,53 @@ mod tests {
         assert_eq!(mapped_instructions[0].operands, vec![42]);
     }
 
+    #[test]
+    fn test_opcode_mapper_memory_intrinsics() {
+        let mapper = OpcodeMapper::new(VmArchitecture::Arch64);
+
+        let copy = mapper.map_instruction(&WasmInstruction::MemoryCopy { dst_memory: 1, src_memory: 0 }).unwrap();
+        assert_eq!(copy.len(), 1);
+        assert_eq!(copy[0].opcode, "memory.copy");
+        assert_eq!(copy[0].operands, vec![1, 0]);
+
+        let init = mapper.map_instruction(&WasmInstruction::MemoryInit { data_index: 3, memory: 1 }).unwrap();
+        assert_eq!((init[0].opcode.as_str(), init[0].operands.clone()), ("memory.init", vec![1, 3]));
+
+        let load = mapper
+            .map_instruction(&WasmInstruction::I32Load {
+                memarg: MemArg::new(16, 2).with_memory(1),
+            })
+            .unwrap();
+        assert_eq!(load[0].operands, vec![1, 16]);
+    }
+
+    #[test]
+    fn test_opcode_mapper_wide_architectures() {
+        for architecture in [VmArchitecture::Arch128, VmArchitecture::Arch256, VmArchitecture::Arch512] {
+            let mapper = OpcodeMapper::new(architecture);
+            assert_eq!(mapper.map_instruction(&WasmInstruction::I64Mul).unwrap()[0].opcode, "WMUL");
+            assert_eq!(mapper.map_instruction(&WasmInstruction::I64ShrS).unwrap()[0].opcode, "WSHRS");
+            // 32-bit arithmetic keeps its width
+            assert_eq!(mapper.map_instruction(&WasmInstruction::I32Add).unwrap()[0].opcode, "i32.add");
+        }
+
+        let mapper = OpcodeMapper::new(VmArchitecture::Arch64);
+        assert_eq!(mapper.map_instruction(&WasmInstruction::I64Mul).unwrap()[0].opcode, "i64.mul");
+    }
+
     #[test]
     fn test_feature_detection() {
         let simd_inst = WasmInstruction::V128Load { memarg: MemArg::default() };
         let features = FeatureDetector::detect_features(&simd_inst);
         assert!(features.contains(&"simd".to_string()));
 
-        let bulk_inst = WasmInstruction::MemoryCopy;
+        let bulk_inst = WasmInstruction::MemoryCopy { dst_memory: 0, src_memory: 0 };
         let features = FeatureDetector::detect_features(&bulk_inst);
         assert!(features.contains(&"bulk_memory".to_string()));
+        assert!(!features.contains(&"multi_memory".to_string()));
+
+        let cross_memory_inst = WasmInstruction::MemoryCopy { dst_memory: 1, src_memory: 0 };
+        assert!(FeatureDetector::requires_feature(&cross_memory_inst, "multi_memory"));
 
         let ref_inst = WasmInstruction::RefNull { ref_type: WasmValueType::FuncRef };
         let features = FeatureDetector::detect_features(&ref_inst);
@@ -0,0 +1,67 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Dependency analyzers used through the crate's public module tree
+
+use dotvm_compiler::dependency_analysis::analyzers::Analyzer;
+use dotvm_compiler::dependency_analysis::analyzers::control_flow::{ControlFlowGraphBuilder, ControlFlowNode, DominanceAnalyzer, LoopDetector, ReachabilityAnalyzer, Terminator};
+use dotvm_compiler::dependency_analysis::analyzers::data_flow::{DataFlowAnalyzer, DataFlowIssueType};
+use dotvm_compiler::dependency_analysis::analyzers::state_access::{ReentrancyAnalyzer, StateAccessType};
+
+#[test]
+fn test_control_flow_analysis_of_a_loop() {
+    // 1 loops back from 2 and leaves to 3; 4 is never entered
+    let terminators = [
+        Terminator::FallThrough,
+        Terminator::Branch { target: 3, condition: None },
+        Terminator::Jump(1),
+        Terminator::Return,
+        Terminator::Return,
+    ];
+    let cfg = ControlFlowGraphBuilder::build(terminators.into_iter().enumerate().map(|(id, t)| ControlFlowNode::new(id, t)).collect()).unwrap();
+
+    assert_eq!(cfg.loops().len(), 1);
+    assert_eq!((cfg.loops()[0].header, cfg.loops()[0].body.as_slice()), (1, [1, 2].as_slice()));
+    assert_eq!(LoopDetector::detect(&cfg).loops.len(), 1);
+    assert!(DominanceAnalyzer::compute(&cfg).dominates(1, 3));
+    assert_eq!(ReachabilityAnalyzer::find_unreachable(&cfg), [4]);
+}
+
+#[test]
+fn test_taint_analysis_of_a_state_write() {
+    let source = "fn store(value: u64) {
+    let doubled = value * 2;
+    set_state(\"value\", doubled);
+}";
+
+    let issues = DataFlowAnalyzer::default().analyze(source).unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!((issues[0].issue_type.clone(), issues[0].location.as_str()), (DataFlowIssueType::TaintedStateWrite, "line 3"));
+}
+
+#[test]
+fn test_reentrancy_analysis_of_a_withdrawal() {
+    let source = r#"fn withdraw(amount: u64) {
+    let balance = get_state("balance");
+    call_dot("payee", amount);
+    set_state("balance", balance - amount);
+}"#;
+
+    let conflicts = ReentrancyAnalyzer::default().analyze(source).unwrap();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].location, "balance");
+    assert_eq!((conflicts[0].first_access.clone(), conflicts[0].second_access.clone()), (StateAccessType::Read, StateAccessType::Write));
+}
@@ -19,9 +19,15 @@
 //! These tests verify the end-to-end functionality from Wasm input
 //! to optimized DotVM bytecode output.
 
-use dotvm_compiler::{codegen::DotVMGenerator, optimizer::Optimizer, transpiler::engine_new::NewTranspilationEngine, transpiler::types::TranspiledModule, wasm::ast::*};
+use dotvm_compiler::{
+    codegen::DotVMGenerator,
+    optimizer::Optimizer,
+    transpiler::engine_new::NewTranspilationEngine,
+    transpiler::types::{Operand, TranspiledModule},
+    wasm::ast::*,
+};
 use dotvm_core::bytecode::VmArchitecture;
-use wasm_encoder::{CodeSection, Function, FunctionSection, Instruction, Module, TypeSection};
+use wasm_encoder::{CodeSection, ExportKind, ExportSection, Function, FunctionSection, Instruction, Module, TypeSection};
 
 /// Test the complete pipeline with a simple arithmetic function
 #[test]
@@ -46,6 +52,8 @@ fn test_complete_pipeline_arithmetic() {
             memory_layout: transpiled_module.memory_layout,
             exports: transpiled_module.exports,
             imports: transpiled_module.imports,
+            element_segments: transpiled_module.element_segments,
+            start_function: transpiled_module.start_function,
             metadata: transpiled_module.metadata,
         };
 
@@ -182,6 +190,8 @@ fn test_performance_large_module() {
         memory_layout: transpiled_module.memory_layout,
         exports: transpiled_module.exports,
         imports: transpiled_module.imports,
+        element_segments: transpiled_module.element_segments,
+        start_function: transpiled_module.start_function,
         metadata: transpiled_module.metadata,
     };
 
@@ -220,6 +230,51 @@ fn test_bytecode_compatibility() {
     }
 }
 
+/// Test that transpiling inlines small callees and drops them once nothing calls them
+#[test]
+fn test_transpile_inlines_small_functions() {
+    let wasm_module_bytes = encode_wasm_module(&create_inlining_test_module());
+
+    let mut transpiler = NewTranspilationEngine::with_architecture(VmArchitecture::Arch64).expect("Transpiler creation should succeed");
+    let transpiled_module = transpiler.transpile(&wasm_module_bytes).expect("Transpilation should succeed");
+
+    assert_eq!(transpiled_module.functions.len(), 1, "The inlined callee should be eliminated");
+    let run = &transpiled_module.functions[0];
+    let opcodes: Vec<&str> = run.instructions.iter().map(|instruction| instruction.opcode.as_str()).collect();
+    assert!(!opcodes.contains(&"call"), "The call should be inlined: {opcodes:?}");
+    assert!(opcodes.contains(&"i32.add"), "The callee's body should be in the caller: {opcodes:?}");
+    assert_eq!(transpiled_module.exports[0].index, 0);
+
+    let mut generator = DotVMGenerator::with_architecture(VmArchitecture::Arch64).expect("Generator creation should succeed");
+    let bytecode = generator.generate_bytecode(&transpiled_module).expect("Bytecode generation should succeed");
+    assert!(!bytecode.is_empty());
+}
+
+/// Test that dropping a dead function renumbers the calls the translator emitted to the ones after it
+#[test]
+fn test_transpile_renumbers_calls_past_an_eliminated_function() {
+    let wasm_module_bytes = encode_wasm_module(&create_dead_function_test_module());
+
+    let mut transpiler = NewTranspilationEngine::with_architecture(VmArchitecture::Arch64).expect("Transpiler creation should succeed");
+    let transpiled_module = transpiler.transpile(&wasm_module_bytes).expect("Transpilation should succeed");
+
+    // `unused` sat between `run` and the recursive `countdown`, which now takes its index
+    assert_eq!(transpiled_module.functions.len(), 2, "The uncalled function should be eliminated");
+    let calls: Vec<_> = transpiled_module
+        .functions
+        .iter()
+        .flat_map(|function| &function.instructions)
+        .filter(|instruction| instruction.opcode == "call")
+        .map(|instruction| instruction.operands.clone())
+        .collect();
+    assert_eq!(calls.len(), 2, "Both calls to the recursive callee should survive: {calls:?}");
+    assert!(
+        calls.iter().all(|operands| operands == &vec![Operand::immediate(1)]),
+        "Calls should target the callee's new index: {calls:?}"
+    );
+    assert_eq!(transpiled_module.exports[0].index, 0);
+}
+
 // Helper functions to create test modules
 
 fn create_simple_arithmetic_module() -> WasmModule {
@@ -436,6 +491,81 @@ fn create_large_test_module(function_count: usize) -> WasmModule {
     }
 }
 
+fn create_inlining_test_module() -> WasmModule {
+    let func_type = WasmFunctionType {
+        params: vec![WasmValueType::I32],
+        results: vec![WasmValueType::I32],
+    };
+
+    WasmModule {
+        types: vec![func_type.clone()],
+        function_types: vec![0, 0],
+        functions: vec![
+            WasmFunction {
+                signature: func_type.clone(),
+                locals: vec![],
+                body: vec![WasmInstruction::LocalGet { local_index: 0 }, WasmInstruction::Call { function_index: 1 }],
+            },
+            WasmFunction {
+                signature: func_type,
+                locals: vec![],
+                body: vec![WasmInstruction::LocalGet { local_index: 0 }, WasmInstruction::I32Const { value: 1 }, WasmInstruction::I32Add],
+            },
+        ],
+        imports: vec![],
+        exports: vec![WasmExport {
+            name: "run".to_string(),
+            kind: WasmExportKind::Function,
+            index: 0,
+        }],
+        memories: vec![],
+        tables: vec![],
+        globals: vec![],
+        start_function: None,
+        elements: vec![],
+        data_segments: vec![],
+        custom_sections: vec![],
+    }
+}
+
+fn create_dead_function_test_module() -> WasmModule {
+    let func_type = WasmFunctionType {
+        params: vec![WasmValueType::I32],
+        results: vec![WasmValueType::I32],
+    };
+    let function = |body| WasmFunction {
+        signature: func_type.clone(),
+        locals: vec![],
+        body,
+    };
+
+    WasmModule {
+        types: vec![func_type.clone()],
+        function_types: vec![0, 0, 0],
+        functions: vec![
+            // run: calls countdown
+            function(vec![WasmInstruction::LocalGet { local_index: 0 }, WasmInstruction::Call { function_index: 2 }]),
+            // unused: never called or exported
+            function(vec![WasmInstruction::LocalGet { local_index: 0 }, WasmInstruction::I32Const { value: 2 }, WasmInstruction::I32Add]),
+            // countdown: recursive, so it is never inlined
+            function(vec![WasmInstruction::LocalGet { local_index: 0 }, WasmInstruction::Call { function_index: 2 }]),
+        ],
+        imports: vec![],
+        exports: vec![WasmExport {
+            name: "run".to_string(),
+            kind: WasmExportKind::Function,
+            index: 0,
+        }],
+        memories: vec![],
+        tables: vec![],
+        globals: vec![],
+        start_function: None,
+        elements: vec![],
+        data_segments: vec![],
+        custom_sections: vec![],
+    }
+}
+
 fn create_compatibility_test_module() -> WasmModule {
     let func_type = WasmFunctionType {
         params: vec![WasmValueType::I32, WasmValueType::F32],
@@ -520,6 +650,21 @@ fn encode_wasm_module(wasm_module: &WasmModule) -> Vec<u8> {
         module.section(&functions);
     }
 
+    // Add export section
+    if !wasm_module.exports.is_empty() {
+        let mut exports = ExportSection::new();
+        for export in &wasm_module.exports {
+            let kind = match export.kind {
+                WasmExportKind::Function => ExportKind::Func,
+                WasmExportKind::Table => ExportKind::Table,
+                WasmExportKind::Memory => ExportKind::Memory,
+                WasmExportKind::Global => ExportKind::Global,
+            };
+            exports.export(&export.name, kind, export.index);
+        }
+        module.section(&exports);
+    }
+
     // Add code section
     if !wasm_module.functions.is_empty() {
         let mut code = CodeSection::new();
@@ -550,6 +695,9 @@ fn encode_wasm_module(wasm_module: &WasmModule) -> Vec<u8> {
                     WasmInstruction::Drop => {
                         function.instruction(&Instruction::Drop);
                     }
+                    WasmInstruction::Call { function_index } => {
+                        function.instruction(&Instruction::Call(*function_index));
+                    }
                     _ => {
                         // For unsupported instructions, add a nop
                         function.instruction(&Instruction::Nop);
@@ -0,0 +1,167 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Tests that the compiled pattern matcher finds exactly what the per-pattern scanning
+//! matcher it replaced found
+
+#[path = "support/pattern_baseline.rs"]
+mod pattern_baseline;
+
+use dotvm_compiler::dependency_analysis::detection::pattern_matcher::{MatchResult, Pattern, PatternMatcher};
+use pattern_baseline::{ScanningMatcher, pattern_library, representative_module};
+
+fn assert_same_matches(patterns: &[Pattern], text: &str, max_matches: usize, allow_overlapping: bool) {
+    let mut compiled = PatternMatcher::new().with_max_matches(max_matches).with_overlapping(allow_overlapping);
+    let mut scanning = ScanningMatcher::new(max_matches, allow_overlapping);
+    for pattern in patterns {
+        compiled.add_pattern(pattern.clone());
+        scanning.add_pattern(pattern.clone());
+    }
+
+    let actual = compiled.find_matches(text);
+    let expected = scanning.find_matches(text);
+    assert_eq!(actual.len(), expected.len(), "match count for {text:?} (max {max_matches}, overlapping {allow_overlapping})");
+    for (actual, expected) in actual.iter().zip(&expected) {
+        assert_same_match(actual, expected);
+    }
+}
+
+fn assert_same_match(actual: &MatchResult, expected: &MatchResult) {
+    assert_eq!(actual.pattern.pattern, expected.pattern.pattern);
+    assert_eq!(actual.pattern.pattern_type, expected.pattern.pattern_type);
+    assert_eq!(actual.pattern.case_sensitive, expected.pattern.case_sensitive);
+    assert_eq!(actual.pattern.priority, expected.pattern.priority);
+    assert_eq!(actual.matched_text, expected.matched_text);
+    assert_eq!(actual.start_position, expected.start_position);
+    assert_eq!(actual.end_position, expected.end_position);
+    assert_eq!(actual.captured_groups, expected.captured_groups);
+    // Scores of matches in empty text are NaN on both sides
+    assert!(
+        actual.score == expected.score || (actual.score.is_nan() && expected.score.is_nan()),
+        "score {} != {}",
+        actual.score,
+        expected.score
+    );
+}
+
+#[test]
+fn test_representative_module() {
+    let patterns = pattern_library();
+    let module = representative_module(20);
+
+    for allow_overlapping in [false, true] {
+        for max_matches in [1, 10, 250, 1000, usize::MAX] {
+            assert_same_matches(&patterns, &module, max_matches, allow_overlapping);
+        }
+    }
+}
+
+#[test]
+fn test_every_pattern_type() {
+    let patterns = vec![
+        Pattern::exact("import env".to_string()),
+        Pattern::exact("IMPORT ENV".to_string()).case_sensitive(false),
+        Pattern::prefix("import".to_string()).with_priority(2),
+        Pattern::suffix("env".to_string()).with_priority(1),
+        Pattern::suffix("ENV".to_string()).case_sensitive(false),
+        Pattern::contains("o".to_string()),
+        Pattern::contains("N".to_string()).case_sensitive(false).with_priority(3),
+        Pattern::regex(r"(\w+) (\w+)".to_string()),
+        Pattern::regex(r"(?i)(IMP)(X)?".to_string()).with_priority(1),
+        Pattern::regex("[invalid".to_string()),
+        Pattern::wildcard("imp?rt *".to_string()),
+        Pattern::wildcard("IMP*".to_string()).case_sensitive(false),
+        Pattern::wildcard("(*".to_string()),
+    ];
+
+    for text in ["import env", "Import Env", "env", "import", "no match here", "nnnn", "", "ÄÖ import ENV"] {
+        for allow_overlapping in [false, true] {
+            for max_matches in [1, 3, 1000] {
+                assert_same_matches(&patterns, text, max_matches, allow_overlapping);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_self_overlapping_and_duplicate_patterns() {
+    let patterns = vec![
+        Pattern::contains("aa".to_string()),
+        Pattern::contains("aa".to_string()).with_priority(1),
+        Pattern::contains("AA".to_string()).case_sensitive(false),
+        Pattern::contains("aaa".to_string()),
+        Pattern::prefix("aa".to_string()),
+        Pattern::suffix("aa".to_string()),
+        Pattern::regex("a+".to_string()),
+        Pattern::regex("a+".to_string()).with_priority(1),
+    ];
+
+    for text in ["aaaa", "aAaAa", "baaab", "a"] {
+        for allow_overlapping in [false, true] {
+            assert_same_matches(&patterns, text, 1000, allow_overlapping);
+        }
+    }
+}
+
+#[test]
+fn test_generated_cases() {
+    // Deterministic linear congruential generator so failures reproduce
+    let mut state: u64 = 0x5eed;
+    let mut next = move |bound: usize| {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        ((state >> 33) as usize) % bound
+    };
+    let alphabet = ['a', 'b', 'A', 'B', ' ', '_'];
+
+    for _ in 0..200 {
+        let word = |next: &mut dyn FnMut(usize) -> usize, max_len: usize| (0..1 + next(max_len)).map(|_| alphabet[next(alphabet.len())]).collect::<String>();
+        let text = word(&mut next, 24);
+        let patterns: Vec<Pattern> = (0..1 + next(8))
+            .map(|_| {
+                let source = word(&mut next, 3);
+                let pattern = match next(6) {
+                    0 => Pattern::exact(source),
+                    1 => Pattern::prefix(source),
+                    2 => Pattern::suffix(source),
+                    3 => Pattern::contains(source),
+                    4 => Pattern::regex(format!("({source})+")),
+                    _ => Pattern::wildcard(format!("*{source}?")),
+                };
+                pattern.case_sensitive(next(2) == 0).with_priority(next(3) as u32)
+            })
+            .collect();
+
+        assert_same_matches(&patterns, &text, 1 + next(6), next(2) == 0);
+    }
+}
+
+#[test]
+fn test_adding_patterns_after_matching() {
+    let mut compiled = PatternMatcher::new();
+    let mut scanning = ScanningMatcher::new(1000, false);
+    let module = representative_module(2);
+
+    for pattern in pattern_library().into_iter().take(40) {
+        compiled.add_pattern(pattern.clone());
+        scanning.add_pattern(pattern);
+        let actual = compiled.find_matches(&module);
+        let expected = scanning.find_matches(&module);
+        assert_eq!(actual.len(), expected.len());
+        for (actual, expected) in actual.iter().zip(&expected) {
+            assert_same_match(actual, expected);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Baseline for the compiled pattern matcher
+//!
+//! `ScanningMatcher` is the matcher `PatternMatcher` replaced: it makes a separate pass
+//! over the text for every pattern. It is kept so tests can check the compiled matcher
+//! returns the same matches and the benchmark can measure the difference. The pattern
+//! library and module below resemble what dependency detection runs on.
+
+use dotvm_compiler::dependency_analysis::detection::pattern_matcher::{MatchResult, Pattern, PatternType};
+use std::collections::{HashMap, HashSet};
+
+/// Per-pattern scanning matcher
+pub struct ScanningMatcher {
+    patterns: Vec<Pattern>,
+    regex_cache: HashMap<String, regex::Regex>,
+    max_matches: usize,
+    allow_overlapping: bool,
+}
+
+impl ScanningMatcher {
+    pub fn new(max_matches: usize, allow_overlapping: bool) -> Self {
+        Self {
+            patterns: Vec::new(),
+            regex_cache: HashMap::new(),
+            max_matches,
+            allow_overlapping,
+        }
+    }
+
+    pub fn add_pattern(&mut self, pattern: Pattern) {
+        self.patterns.push(pattern);
+        self.patterns.sort_by_key(|pattern| std::cmp::Reverse(pattern.priority));
+    }
+
+    pub fn find_matches(&mut self, text: &str) -> Vec<MatchResult> {
+        let mut matches = Vec::new();
+        let mut used_positions = HashSet::new();
+
+        let patterns = self.patterns.clone();
+        for pattern in &patterns {
+            for match_result in self.find_pattern_matches(text, pattern) {
+                if !self.allow_overlapping {
+                    let overlaps = (match_result.start_position..match_result.end_position).any(|pos| used_positions.contains(&pos));
+                    if overlaps {
+                        continue;
+                    }
+                }
+                for pos in match_result.start_position..match_result.end_position {
+                    used_positions.insert(pos);
+                }
+                matches.push(match_result);
+                if matches.len() >= self.max_matches {
+                    break;
+                }
+            }
+            if matches.len() >= self.max_matches {
+                break;
+            }
+        }
+
+        matches.sort_by_key(|m| m.start_position);
+        matches
+    }
+
+    fn find_pattern_matches(&mut self, text: &str, pattern: &Pattern) -> Vec<MatchResult> {
+        let search_text = if pattern.case_sensitive { text.to_string() } else { text.to_lowercase() };
+        let search_pattern = if pattern.case_sensitive { pattern.pattern.clone() } else { pattern.pattern.to_lowercase() };
+        let text_len = search_text.len() as f64;
+        let result = |matched_text: &str, start: usize, score: f64| MatchResult {
+            pattern: pattern.clone(),
+            matched_text: matched_text.to_string(),
+            start_position: start,
+            end_position: start + matched_text.len(),
+            captured_groups: Vec::new(),
+            score,
+        };
+
+        match pattern.pattern_type {
+            PatternType::Exact if search_text == search_pattern => vec![result(&search_text, 0, 1.0)],
+            PatternType::Prefix if search_text.starts_with(&search_pattern) => vec![result(&search_pattern, 0, search_pattern.len() as f64 / text_len)],
+            PatternType::Suffix if search_text.ends_with(&search_pattern) => {
+                vec![result(&search_pattern, search_text.len() - search_pattern.len(), search_pattern.len() as f64 / text_len)]
+            }
+            PatternType::Contains => {
+                let mut matches = Vec::new();
+                let mut start = 0;
+                while let Some(pos) = search_text[start..].find(&search_pattern) {
+                    let absolute_pos = start + pos;
+                    matches.push(result(&search_pattern, absolute_pos, search_pattern.len() as f64 / text_len));
+                    start = absolute_pos + 1;
+                }
+                matches
+            }
+            PatternType::Regex => {
+                if !self.regex_cache.contains_key(&pattern.pattern) {
+                    match regex::Regex::new(&pattern.pattern) {
+                        Ok(regex) => self.regex_cache.insert(pattern.pattern.clone(), regex),
+                        Err(_) => return Vec::new(),
+                    };
+                }
+                let regex = &self.regex_cache[&pattern.pattern];
+                regex
+                    .captures_iter(text)
+                    .filter_map(|capture| {
+                        let full_match = capture.get(0)?;
+                        Some(MatchResult {
+                            pattern: pattern.clone(),
+                            matched_text: full_match.as_str().to_string(),
+                            start_position: full_match.start(),
+                            end_position: full_match.end(),
+                            captured_groups: capture.iter().skip(1).filter_map(|m| m.map(|m| m.as_str().to_string())).collect(),
+                            score: full_match.len() as f64 / text.len() as f64,
+                        })
+                    })
+                    .collect()
+            }
+            PatternType::Wildcard => {
+                let regex_pattern = search_pattern.replace("*", ".*").replace("?", ".");
+                match regex::Regex::new(&format!("^{}$", regex_pattern)) {
+                    Ok(regex) if regex.is_match(&search_text) => vec![result(&search_text, 0, 1.0)],
+                    _ => Vec::new(),
+                }
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Host functions and globals a transpiled module refers to
+const IMPORTS: [&str; 8] = ["db_get", "db_put", "db_scan", "state_read", "state_write", "emit_event", "call_dot", "crypto_hash"];
+
+/// A pattern library of about 400 patterns mixing every pattern type
+pub fn pattern_library() -> Vec<Pattern> {
+    let mut patterns = Vec::new();
+    for i in 0..80 {
+        let import = IMPORTS[i % IMPORTS.len()];
+        patterns.push(Pattern::contains(format!("call ${}_{}", import, i)).with_priority((i % 5) as u32));
+        patterns.push(Pattern::contains(format!("global.get $g{}", i)).case_sensitive(i % 3 != 0));
+        patterns.push(Pattern::contains(format!("LOCAL.SET {}", i)).case_sensitive(false).with_priority(2));
+        patterns.push(Pattern::prefix(format!(";; module {}", i % 10)).with_priority(1));
+        patterns.push(Pattern::suffix(format!("end_{})", i % 20)));
+    }
+    for i in 0..20 {
+        patterns.push(Pattern::exact(format!("(module $m{})", i)));
+        patterns.push(Pattern::regex(format!(r"call \$({})_(\d*{})\b", IMPORTS[i % IMPORTS.len()], i)).with_priority(3));
+        patterns.push(Pattern::wildcard(format!(";; module {}*", i % 10)).case_sensitive(i % 2 == 0));
+    }
+    patterns.push(Pattern::regex(r"i32\.const (\d+)".to_string()).with_priority(4));
+    patterns.push(Pattern::regex("unclosed (group".to_string()));
+    patterns
+}
+
+/// A transpiled module of roughly `functions` × 30 lines
+pub fn representative_module(functions: usize) -> String {
+    let mut module = String::from(";; module 7 transpiled from wasm\n(module $m7\n");
+    for f in 0..functions {
+        module.push_str(&format!("  (func $f{} (param i32 i64) (result i32)\n", f));
+        for line in 0..28 {
+            let n = f * 31 + line;
+            let statement = match n % 7 {
+                0 => format!("call ${}_{}", IMPORTS[n % IMPORTS.len()], n % 97),
+                1 => format!("global.get $g{}", n % 83),
+                2 => format!("local.set {}", n % 89),
+                3 => format!("i32.const {}", n),
+                4 => format!("Local.Set {}", n % 61),
+                5 => "i64.add".to_string(),
+                _ => format!("br_if {}", n % 3),
+            };
+            module.push_str("    ");
+            module.push_str(&statement);
+            module.push('\n');
+        }
+        module.push_str("  )\n");
+    }
+    module.push_str(")\n;; end_7)");
+    module
+}
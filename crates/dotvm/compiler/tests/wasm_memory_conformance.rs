@@ -0,0 +1,365 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Conformance tests for bulk memory and multi-memory modules
+//!
+//! Each fixture is assembled with `wasm-encoder` from the WAT shown above its test, parsed
+//! into the WASM AST and run through the transpiler.
+
+use dotvm_compiler::{
+    transpiler::{
+        config::{FeatureFlags, TranspilationConfig},
+        engine_new::NewTranspilationEngine,
+        types::{Operand, TranspiledInstruction, TranspiledModule},
+    },
+    wasm::{WasmError, WasmParser, ast::*},
+};
+use wasm_encoder::{
+    CodeSection, ConstExpr, DataCountSection, DataSection, EntityType, ExportKind, ExportSection, Function, FunctionSection, ImportSection, Instruction, MemArg as EncMemArg, MemorySection,
+    MemoryType, Module, TypeSection, ValType,
+};
+
+/// Pieces of a single-function module taking three `i32` parameters
+#[derive(Default)]
+struct Fixture {
+    imported_function: bool,
+    imported_memory: Option<(u64, Option<u64>)>,
+    memories: Vec<(u64, Option<u64>)>,
+    passive_data: Option<&'static [u8]>,
+    body: Vec<Instruction<'static>>,
+}
+
+impl Fixture {
+    fn with_body(memories: Vec<(u64, Option<u64>)>, body: Vec<Instruction<'static>>) -> Self {
+        Self { memories, body, ..Default::default() }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let memory_type = |(minimum, maximum): (u64, Option<u64>)| MemoryType {
+            minimum,
+            maximum,
+            memory64: false,
+            shared: false,
+        };
+        let mut module = Module::new();
+
+        let mut types = TypeSection::new();
+        types.function([ValType::I32, ValType::I32, ValType::I32], []);
+        module.section(&types);
+
+        let mut imports = ImportSection::new();
+        if self.imported_function {
+            imports.import("env", "log", EntityType::Function(0));
+        }
+        if let Some(memory) = self.imported_memory {
+            imports.import("env", "heap", EntityType::Memory(memory_type(memory)));
+        }
+        if !imports.is_empty() {
+            module.section(&imports);
+        }
+
+        let mut functions = FunctionSection::new();
+        functions.function(0);
+        module.section(&functions);
+
+        if !self.memories.is_empty() {
+            let mut memories = MemorySection::new();
+            for &memory in &self.memories {
+                memories.memory(memory_type(memory));
+            }
+            module.section(&memories);
+        }
+
+        let mut exports = ExportSection::new();
+        exports.export("run", ExportKind::Func, u32::from(self.imported_function));
+        module.section(&exports);
+
+        if self.passive_data.is_some() {
+            module.section(&DataCountSection { count: 2 });
+        }
+
+        let mut function = Function::new([]);
+        for instruction in &self.body {
+            function.instruction(instruction);
+        }
+        function.instruction(&Instruction::End);
+        let mut code = CodeSection::new();
+        code.function(&function);
+        module.section(&code);
+
+        if let Some(bytes) = self.passive_data {
+            let mut data = DataSection::new();
+            data.active(0, &ConstExpr::i32_const(0), b"header".iter().copied());
+            data.passive(bytes.iter().copied());
+            module.section(&data);
+        }
+
+        module.finish()
+    }
+
+    fn parse(&self) -> WasmModule {
+        WasmParser::new().parse(&self.encode()).expect("fixture should parse")
+    }
+
+    fn transpile(&self) -> TranspiledModule {
+        NewTranspilationEngine::new(TranspilationConfig::default())
+            .unwrap()
+            .transpile(&self.encode())
+            .expect("fixture should transpile")
+    }
+
+    fn transpile_error(&self, config: TranspilationConfig) -> String {
+        match NewTranspilationEngine::new(config).unwrap().transpile(&self.encode()) {
+            Ok(_) => panic!("fixture should be rejected"),
+            Err(error) => error.to_string(),
+        }
+    }
+}
+
+const PARAMS: [Instruction<'static>; 3] = [Instruction::LocalGet(0), Instruction::LocalGet(1), Instruction::LocalGet(2)];
+
+fn params_then(instructions: impl IntoIterator<Item = Instruction<'static>>) -> Vec<Instruction<'static>> {
+    PARAMS.into_iter().chain(instructions).collect()
+}
+
+fn mem_arg(offset: u64, align: u32, memory_index: u32) -> EncMemArg {
+    EncMemArg { offset, align, memory_index }
+}
+
+/// The single memory instruction of the transpiled function with this opcode
+fn only(module: &TranspiledModule, opcode: &str) -> TranspiledInstruction {
+    let matches: Vec<_> = module.functions[0].instructions.iter().filter(|instruction| instruction.opcode == opcode).collect();
+    assert_eq!(matches.len(), 1, "expected one {opcode} in {:?}", module.functions[0].instructions);
+    matches[0].clone()
+}
+
+fn immediates(values: &[u32]) -> Vec<Operand> {
+    values.iter().map(|&value| Operand::immediate(value)).collect()
+}
+
+/// Bulk operations must stay one intrinsic each, never an expanded byte loop
+fn assert_no_loops(module: &TranspiledModule) {
+    for instruction in &module.functions[0].instructions {
+        assert!(!matches!(instruction.opcode.as_str(), "loop" | "br" | "br_if"), "unexpected {}", instruction.opcode);
+    }
+}
+
+/// ```wat
+/// (module
+///   (memory 1)
+///   (func (export "run") (param i32 i32 i32)
+///     (memory.copy (local.get 0) (local.get 1) (local.get 2))))
+/// ```
+#[test]
+fn test_memory_copy() {
+    let fixture = Fixture::with_body(vec![(1, None)], params_then([Instruction::MemoryCopy { src_mem: 0, dst_mem: 0 }]));
+
+    let body = &fixture.parse().functions[0].body;
+    assert_eq!(body[3], WasmInstruction::MemoryCopy { dst_memory: 0, src_memory: 0 });
+
+    let module = fixture.transpile();
+    assert_eq!(only(&module, "memory.copy").operands, immediates(&[0, 0]));
+    assert_no_loops(&module);
+}
+
+/// ```wat
+/// (module
+///   (memory 1)
+///   (func (export "run") (param i32 i32 i32)
+///     (memory.fill (local.get 0) (local.get 1) (local.get 2))))
+/// ```
+#[test]
+fn test_memory_fill() {
+    let fixture = Fixture::with_body(vec![(1, None)], params_then([Instruction::MemoryFill(0)]));
+
+    assert_eq!(fixture.parse().functions[0].body[3], WasmInstruction::MemoryFill { memory: 0 });
+
+    let module = fixture.transpile();
+    assert_eq!(only(&module, "memory.fill").operands, immediates(&[0]));
+    assert_no_loops(&module);
+}
+
+/// ```wat
+/// (module
+///   (memory 1)
+///   (data (i32.const 0) "header")
+///   (data $payload "\01\02\03\04")
+///   (func (export "run") (param i32 i32 i32)
+///     (memory.init $payload (local.get 0) (local.get 1) (local.get 2))
+///     (data.drop $payload)))
+/// ```
+#[test]
+fn test_memory_init_and_data_drop() {
+    let fixture = Fixture {
+        memories: vec![(1, None)],
+        passive_data: Some(&[1, 2, 3, 4]),
+        body: params_then([Instruction::MemoryInit { mem: 0, data_index: 1 }, Instruction::DataDrop(1)]),
+        ..Default::default()
+    };
+
+    let parsed = fixture.parse();
+    assert_eq!(parsed.data_segments.len(), 2);
+    assert_eq!(parsed.data_segments[1].data, vec![1, 2, 3, 4]);
+    assert_eq!(
+        parsed.functions[0].body[3..5],
+        [WasmInstruction::MemoryInit { data_index: 1, memory: 0 }, WasmInstruction::DataDrop { data_index: 1 }]
+    );
+
+    let module = fixture.transpile();
+    assert_eq!(only(&module, "memory.init").operands, immediates(&[0, 1]));
+    assert_eq!(only(&module, "data.drop").operands, immediates(&[1]));
+}
+
+/// ```wat
+/// (module
+///   (import "env" "heap" (memory 1 4))
+///   (memory 2)
+///   (func (export "run") (param i32 i32 i32)
+///     (memory.copy 1 0 (local.get 0) (local.get 1) (local.get 2))))
+/// ```
+#[test]
+fn test_cross_memory_copy() {
+    let fixture = Fixture {
+        imported_memory: Some((1, Some(4))),
+        memories: vec![(2, None)],
+        body: params_then([Instruction::MemoryCopy { src_mem: 0, dst_mem: 1 }]),
+        ..Default::default()
+    };
+
+    let parsed = fixture.parse();
+    assert_eq!(parsed.total_memory_count(), 2);
+    assert_eq!(parsed.functions[0].body[3], WasmInstruction::MemoryCopy { dst_memory: 1, src_memory: 0 });
+
+    let module = fixture.transpile();
+    assert_eq!(only(&module, "memory.copy").operands, immediates(&[1, 0]));
+    assert_no_loops(&module);
+
+    // Each memory is its own region, the imported one first
+    let layout = &module.memory_layout;
+    assert_eq!(layout.regions.len(), 2);
+    assert_eq!((layout.regions[0].handle, layout.regions[0].initial_pages, layout.regions[0].maximum_pages), (0, 1, Some(4)));
+    assert_eq!((layout.regions[1].handle, layout.regions[1].initial_pages, layout.regions[1].maximum_pages), (1, 2, None));
+}
+
+/// ```wat
+/// (module
+///   (memory $a 1)
+///   (memory $b 1)
+///   (func (export "run") (param i32 i32 i32)
+///     (i32.store $b offset=8 (local.get 0) (i32.load $a (local.get 1)))
+///     (drop (memory.grow $b (i32.const 1)))
+///     (drop (memory.size $b))))
+/// ```
+#[test]
+fn test_multi_memory_access() {
+    let fixture = Fixture::with_body(
+        vec![(1, None), (1, None)],
+        vec![
+            Instruction::LocalGet(0),
+            Instruction::LocalGet(1),
+            Instruction::I32Load(mem_arg(0, 2, 0)),
+            Instruction::I32Store(mem_arg(8, 2, 1)),
+            Instruction::I32Const(1),
+            Instruction::MemoryGrow(1),
+            Instruction::Drop,
+            Instruction::MemorySize(1),
+            Instruction::Drop,
+        ],
+    );
+
+    let body = &fixture.parse().functions[0].body;
+    assert_eq!(body[2], WasmInstruction::I32Load { memarg: MemArg::new(0, 2) });
+    assert_eq!(
+        body[3],
+        WasmInstruction::I32Store {
+            memarg: MemArg::new(8, 2).with_memory(1)
+        }
+    );
+    assert_eq!(body[5], WasmInstruction::MemoryGrow { memory: 1 });
+    assert_eq!(body[7], WasmInstruction::MemorySize { memory: 1 });
+
+    let module = fixture.transpile();
+    assert_eq!(only(&module, "i32.load").operands, immediates(&[0, 0]));
+    assert_eq!(only(&module, "i32.store").operands, immediates(&[1, 8]));
+    assert_eq!(only(&module, "memory.grow").operands, immediates(&[1]));
+    assert_eq!(only(&module, "memory.size").operands, immediates(&[1]));
+    assert_eq!(module.memory_layout.regions.len(), 2);
+}
+
+/// ```wat
+/// (module
+///   (memory 1 1)
+///   (func (export "run") (param i32 i32 i32)
+///     (drop (i32.atomic.load (local.get 0)))))
+/// ```
+#[test]
+fn test_unsupported_proposal_names_feature_and_location() {
+    let fixture = Fixture::with_body(vec![(1, Some(1))], vec![Instruction::LocalGet(0), Instruction::I32AtomicLoad(mem_arg(0, 2, 0)), Instruction::Drop]);
+    let bytes = fixture.encode();
+    let offset = bytes.windows(2).position(|window| window == [0xfe, 0x10]).unwrap();
+
+    let error = WasmParser::new().parse(&bytes).unwrap_err();
+    match &error {
+        WasmError::UnsupportedFeatureAt { feature, function, offset: at, .. } => {
+            assert_eq!((feature.as_str(), *function, *at), ("threads", 0, offset));
+        }
+        other => panic!("unexpected error: {other}"),
+    }
+
+    let message = fixture.transpile_error(TranspilationConfig::default());
+    assert!(message.contains(&format!("Unsupported WASM feature threads in function 0 at offset {:#x}", offset)), "{message}");
+}
+
+/// ```wat
+/// (module
+///   (import "env" "log" (func (param i32 i32 i32)))
+///   (memory 1)
+///   (func (export "run") (param i32 i32 i32)
+///     (memory.fill (local.get 0) (local.get 1) (local.get 2))))
+/// ```
+#[test]
+fn test_bulk_memory_can_be_disabled() {
+    let fixture = Fixture {
+        imported_function: true,
+        memories: vec![(1, None)],
+        body: params_then([Instruction::MemoryFill(0)]),
+        ..Default::default()
+    };
+
+    let mut config = TranspilationConfig::default();
+    config.feature_flags.enable_bulk_memory = false;
+    let message = fixture.transpile_error(config);
+    // The defined function follows the imported one in the function index space
+    assert!(message.contains("Unsupported WASM feature bulk_memory in function 1"), "{message}");
+}
+
+/// ```wat
+/// (module
+///   (memory 1)
+///   (memory 1)
+///   (func (export "run") (param i32 i32 i32)))
+/// ```
+#[test]
+fn test_multi_memory_can_be_disabled() {
+    let fixture = Fixture::with_body(vec![(1, None), (1, None)], Vec::new());
+
+    let config = TranspilationConfig::default().with_feature_flags(FeatureFlags {
+        enable_multi_memory: false,
+        ..FeatureFlags::default()
+    });
+    let message = fixture.transpile_error(config);
+    assert!(message.contains("multi_memory (2 linear memories declared)"), "{message}");
+}
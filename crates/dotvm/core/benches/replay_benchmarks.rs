@@ -0,0 +1,89 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Overhead of recording and replaying host calls
+//!
+//! Runs the same programs without a tape, under a recording tape and from a recorded trace:
+//! one dominated by `DB_GET` host calls against the in-memory bridge, and one of stack
+//! instructions only, which a tape must not slow down.
+
+use criterion::{BatchSize, BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
+use dotvm_core::bytecode::{BytecodeFile, ConstantValue, VmArchitecture};
+use dotvm_core::opcode::db_opcodes::DatabaseOpcode;
+use dotvm_core::opcode::stack_opcodes::StackOpcode;
+use dotvm_core::vm::executor::VmExecutor;
+use dotvm_core::vm::replay::{DEFAULT_MAX_TRACE_BYTES, ExecutionTrace, record, replay, trace_executor};
+
+const DOT_ID: &str = "bench_dot";
+const ITERATIONS: usize = 1_000;
+/// Each database call counts against the dot's limit of 100 open file descriptors
+const HOST_CALLS: usize = 90;
+
+/// Looks up `HOST_CALLS` missing documents, discarding each result
+fn host_call_program() -> BytecodeFile {
+    let mut bytecode = BytecodeFile::new(VmArchitecture::Arch64);
+    let collection = bytecode.add_constant(ConstantValue::String("bench".to_string()));
+    let document = bytecode.add_constant(ConstantValue::String("00000000-0000-0000-0000-000000000000".to_string()));
+    bytecode.add_instruction(StackOpcode::Push.as_u8(), &collection.to_le_bytes());
+    bytecode.add_instruction(DatabaseOpcode::DbCreateCollection.as_u8(), &[]);
+    for _ in 0..HOST_CALLS {
+        bytecode.add_instruction(StackOpcode::Push.as_u8(), &collection.to_le_bytes());
+        bytecode.add_instruction(StackOpcode::Push.as_u8(), &document.to_le_bytes());
+        bytecode.add_instruction(DatabaseOpcode::DbGet.as_u8(), &[]);
+        bytecode.add_instruction(StackOpcode::Pop.as_u8(), &[]);
+    }
+    bytecode
+}
+
+/// Pushes and pops `ITERATIONS` integers
+fn stack_program() -> BytecodeFile {
+    let mut bytecode = BytecodeFile::new(VmArchitecture::Arch64);
+    for _ in 0..ITERATIONS {
+        bytecode.add_instruction(StackOpcode::PushInt8.as_u8(), &[7]);
+        bytecode.add_instruction(StackOpcode::Pop.as_u8(), &[]);
+    }
+    bytecode
+}
+
+fn loaded(bytecode: &BytecodeFile) -> VmExecutor {
+    let mut executor = trace_executor(DOT_ID);
+    executor.load_bytecode(bytecode.clone()).expect("benchmark bytecode loads");
+    executor
+}
+
+fn bench_recording_overhead(c: &mut Criterion) {
+    let mut group = c.benchmark_group("replay_overhead");
+
+    for (name, bytecode) in [("db_get", host_call_program()), ("stack_only", stack_program())] {
+        let trace = ExecutionTrace::new(DOT_ID, 1, record(&mut loaded(&bytecode), DEFAULT_MAX_TRACE_BYTES));
+        assert_eq!(trace.error, None, "benchmark program must run cleanly");
+
+        group.bench_with_input(BenchmarkId::new("untaped", name), &bytecode, |b, bytecode| {
+            b.iter_batched(|| loaded(bytecode), |mut executor| black_box(executor.execute().unwrap()), BatchSize::SmallInput)
+        });
+        group.bench_with_input(BenchmarkId::new("recording", name), &bytecode, |b, bytecode| {
+            b.iter_batched(|| loaded(bytecode), |mut executor| black_box(record(&mut executor, DEFAULT_MAX_TRACE_BYTES)), BatchSize::SmallInput)
+        });
+        group.bench_with_input(BenchmarkId::new("replaying", name), &bytecode, |b, bytecode| {
+            b.iter_batched(|| loaded(bytecode), |mut executor| black_box(replay(&mut executor, &trace)), BatchSize::SmallInput)
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_recording_overhead);
+criterion_main!(benches);
@@ -0,0 +1,133 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Hot state cache benchmarks
+//!
+//! Compares the sharded map behind `SharedMap` with the single
+//! `Arc<RwLock<HashMap>>` it replaced, at 1, 8, 32 and 128 threads where 1% or
+//! 5% of operations are writes. Each thread performs the same mix of lookups and
+//! updates over a shared key space; throughput is total operations per second.
+
+use criterion::{BenchmarkId, Criterion, Throughput, black_box, criterion_group, criterion_main};
+use dotvm_core::vm::state_management::{ShardedMap, StateKey, StateValue};
+use std::collections::HashMap;
+use std::sync::{Arc, Barrier, RwLock};
+use std::time::{Duration, Instant};
+
+/// Distinct keys in the map
+const KEYS: usize = 10_000;
+
+/// Operations each thread performs per benchmark iteration
+const OPS_PER_THREAD: usize = 1_000;
+
+const THREADS: [usize; 4] = [1, 8, 32, 128];
+
+const WRITE_PERCENTAGES: [usize; 2] = [1, 5];
+
+/// The map operations the benchmark exercises
+trait StateCache: Send + Sync {
+    fn read(&self, key: &StateKey) -> Option<usize>;
+    fn write(&self, key: StateKey, value: StateValue);
+}
+
+/// The previous hot state cache: one lock around the whole map
+struct LockedMap(RwLock<HashMap<StateKey, StateValue>>);
+
+impl StateCache for LockedMap {
+    fn read(&self, key: &StateKey) -> Option<usize> {
+        self.0.read().unwrap().get(key).map(|value| value.0.len())
+    }
+
+    fn write(&self, key: StateKey, value: StateValue) {
+        self.0.write().unwrap().insert(key, value);
+    }
+}
+
+impl StateCache for ShardedMap<StateKey, StateValue> {
+    fn read(&self, key: &StateKey) -> Option<usize> {
+        self.get(key).map(|value| value.0.len())
+    }
+
+    fn write(&self, key: StateKey, value: StateValue) {
+        self.insert(key, value);
+    }
+}
+
+fn populate(cache: &dyn StateCache, keys: &[StateKey]) {
+    for key in keys {
+        cache.write(key.clone(), StateValue::new(vec![0; 64]));
+    }
+}
+
+/// Time `iters` rounds of the operation mix on `threads` threads started together
+fn run(cache: Arc<dyn StateCache>, keys: Arc<Vec<StateKey>>, threads: usize, write_percentage: usize, iters: u64) -> Duration {
+    let barrier = Arc::new(Barrier::new(threads + 1));
+    let workers: Vec<_> = (0..threads)
+        .map(|thread| {
+            let (cache, keys, barrier) = (cache.clone(), keys.clone(), barrier.clone());
+            std::thread::spawn(move || {
+                barrier.wait();
+                // Spread threads over the key space with a stride co-prime to it
+                let mut index = thread * 7_919;
+                for op in 0..iters as usize * OPS_PER_THREAD {
+                    index = (index + 7_919) % KEYS;
+                    if op % 100 < write_percentage {
+                        cache.write(keys[index].clone(), StateValue::new(vec![op as u8; 64]));
+                    } else {
+                        black_box(cache.read(&keys[index]));
+                    }
+                }
+            })
+        })
+        .collect();
+
+    barrier.wait();
+    let start = Instant::now();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    start.elapsed()
+}
+
+fn bench_state_cache(c: &mut Criterion) {
+    let keys: Arc<Vec<StateKey>> = Arc::new((0..KEYS).map(|i| StateKey::from_string(&format!("dot/state/{i}"))).collect());
+
+    for write_percentage in WRITE_PERCENTAGES {
+        let mut group = c.benchmark_group(format!("state_cache_{write_percentage}pct_writes"));
+        group.sample_size(10);
+
+        for threads in THREADS {
+            group.throughput(Throughput::Elements((threads * OPS_PER_THREAD) as u64));
+
+            let locked: Arc<dyn StateCache> = Arc::new(LockedMap(RwLock::new(HashMap::new())));
+            populate(locked.as_ref(), &keys);
+            group.bench_with_input(BenchmarkId::new("rwlock_hashmap", threads), &threads, |b, &threads| {
+                b.iter_custom(|iters| run(locked.clone(), keys.clone(), threads, write_percentage, iters))
+            });
+
+            let sharded: Arc<dyn StateCache> = Arc::new(ShardedMap::<StateKey, StateValue>::new());
+            populate(sharded.as_ref(), &keys);
+            group.bench_with_input(BenchmarkId::new("sharded", threads), &threads, |b, &threads| {
+                b.iter_custom(|iters| run(sharded.clone(), keys.clone(), threads, write_percentage, iters))
+            });
+        }
+
+        group.finish();
+    }
+}
+
+criterion_group!(state_cache_benches, bench_state_cache);
+criterion_main!(state_cache_benches);
@@ -27,6 +27,15 @@ pub enum VmArchitecture {
 }
 
 impl VmArchitecture {
+    /// Every architecture, from the narrowest to the widest.
+    pub const ALL: [VmArchitecture; 5] = [
+        VmArchitecture::Arch32,
+        VmArchitecture::Arch64,
+        VmArchitecture::Arch128,
+        VmArchitecture::Arch256,
+        VmArchitecture::Arch512,
+    ];
+
     /// Create a VmArchitecture from a u8 value.
     pub fn from_u8(value: u8) -> Option<Self> {
         match value {
@@ -49,6 +58,31 @@ impl VmArchitecture {
             VmArchitecture::Arch512 => 64,
         }
     }
+
+    /// Get the word size in bits for this architecture.
+    pub fn bit_width(&self) -> u32 {
+        self.word_size() as u32 * 8
+    }
+}
+
+impl std::fmt::Display for VmArchitecture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "arch{}", self.bit_width())
+    }
+}
+
+impl std::str::FromStr for VmArchitecture {
+    type Err = String;
+
+    /// Parse an architecture name such as `arch64`, or its bit width alone.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_ascii_lowercase();
+        let bits = name.strip_prefix("arch").unwrap_or(&name);
+        Self::ALL
+            .into_iter()
+            .find(|arch| arch.bit_width().to_string() == bits)
+            .ok_or_else(|| format!("Unknown VM architecture '{}', expected one of arch32, arch64, arch128, arch256, arch512", s.trim()))
+    }
 }
 
 /// Represents the header of the DotVM bytecode.
@@ -248,6 +282,17 @@ mod tests {
         assert_eq!(VmArchitecture::Arch512.word_size(), 64);
     }
 
+    #[test]
+    fn test_vm_architecture_names() {
+        for arch in VmArchitecture::ALL {
+            assert_eq!(arch.to_string().parse::<VmArchitecture>(), Ok(arch));
+        }
+        assert_eq!(VmArchitecture::Arch128.to_string(), "arch128");
+        assert_eq!(" ARCH256 ".parse::<VmArchitecture>(), Ok(VmArchitecture::Arch256));
+        assert_eq!("64".parse::<VmArchitecture>(), Ok(VmArchitecture::Arch64));
+        assert!("WASM".parse::<VmArchitecture>().unwrap_err().contains("'WASM'"));
+    }
+
     #[test]
     fn test_bytecode_header_new() {
         let header = BytecodeHeader::new(VmArchitecture::Arch64);
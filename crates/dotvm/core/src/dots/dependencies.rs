@@ -22,7 +22,8 @@
 use crate::dots::{DotSegment, ProcessingError};
 use petgraph::algo::toposort;
 use petgraph::graph::{DiGraph, NodeIndex};
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
 
 pub mod strategies;
 
@@ -125,8 +126,44 @@ impl DependencyGraph {
                 let sorted_ids = indices.into_iter().map(|idx| self.graph[idx].clone()).collect();
                 Ok(sorted_ids)
             }
-            Err(_) => Err(ProcessingError::DependencyResolutionFailed("Circular dependency detected in dot segments".to_string())),
+            Err(_) => {
+                let cycle = self.find_cycle().map(|path| format!(": {}", path.join(" -> "))).unwrap_or_default();
+                Err(ProcessingError::DependencyResolutionFailed(format!("Circular dependency detected in dot segments{cycle}")))
+            }
+        }
+    }
+
+    /// Finds a dependency cycle, if the graph has one
+    ///
+    /// # Returns
+    /// Dot segment IDs along the cycle in "depends on" order, starting and
+    /// ending with the same ID (`a -> b -> a` when `a` and `b` require each other)
+    pub fn find_cycle(&self) -> Option<Vec<String>> {
+        let start = toposort(&self.graph, None).err()?.node_id();
+
+        // Walk dependent -> dependency edges breadth-first until we are back at the start
+        let mut came_from: HashMap<NodeIndex, NodeIndex> = HashMap::new();
+        let mut queue = VecDeque::from([start]);
+        while let Some(node) = queue.pop_front() {
+            for next in self.graph.neighbors_directed(node, petgraph::Direction::Incoming) {
+                if next == start {
+                    let mut path = vec![node];
+                    let mut current = node;
+                    while current != start {
+                        current = came_from[&current];
+                        path.push(current);
+                    }
+                    path.reverse();
+                    path.push(start);
+                    return Some(path.into_iter().map(|index| self.graph[index].clone()).collect());
+                }
+                if let Entry::Vacant(entry) = came_from.entry(next) {
+                    entry.insert(node);
+                    queue.push_back(next);
+                }
+            }
         }
+        None
     }
 
     /// Checks if a dot segment has incoming dependencies
@@ -211,10 +248,8 @@ impl DependencyResolver {
             strategy.detect_dependencies(segments, &mut graph)?;
         }
 
-        // Cyclic dependency check
-        if graph.topological_sort().is_err() {
-            return Err(ProcessingError::DependencyResolutionFailed("Circular dependency detected".to_string()));
-        }
+        // Cyclic dependency check, reporting the cycle path
+        graph.topological_sort()?;
 
         Ok(graph)
     }
@@ -288,4 +323,31 @@ mod tests {
 
         assert!(graph.topological_sort().is_err());
     }
+
+    #[test]
+    fn test_find_cycle_reports_path() {
+        let mut graph = DependencyGraph::new();
+        graph.add_dependency("pricing", "tax", DependencyType::Prerequisite);
+        graph.add_dependency("tax", "rates", DependencyType::Prerequisite);
+        graph.add_dependency("invoice", "pricing", DependencyType::Prerequisite);
+        assert_eq!(graph.find_cycle(), None);
+
+        graph.add_dependency("rates", "pricing", DependencyType::Prerequisite);
+        let cycle = graph.find_cycle().unwrap();
+        assert_eq!(cycle.first(), cycle.last());
+        assert_eq!(cycle.len(), 4);
+        // Each step names a dependency of the one before it
+        for step in cycle.windows(2) {
+            assert!(graph.get_dependencies(&step[0]).contains(&step[1]));
+        }
+
+        let ProcessingError::DependencyResolutionFailed(message) = graph.topological_sort().unwrap_err() else {
+            panic!("expected a dependency resolution error");
+        };
+        assert!(message.ends_with(&cycle.join(" -> ")));
+
+        let mut own = DependencyGraph::new();
+        own.add_dependency("tax", "tax", DependencyType::Reference);
+        assert_eq!(own.find_cycle(), Some(vec!["tax".to_string(), "tax".to_string()]));
+    }
 }
@@ -20,6 +20,7 @@ mod page_table;
 mod pool;
 mod protection;
 mod shared_memory;
+mod tracking;
 
 pub use allocator::*;
 pub use error::*;
@@ -27,9 +28,11 @@ pub use page_table::*;
 pub use pool::*;
 pub use protection::*;
 pub use shared_memory::*;
+pub use tracking::*;
 
 use num_bigint::BigUint;
 use std::marker::PhantomData;
+use std::sync::Arc;
 // use std::sync::atomic::Ordering; // Unused import at this level
 
 /// Trait defining architecture-specific memory behaviour
@@ -210,9 +213,34 @@ pub struct MemoryManager<A: Architecture> {
     page_table: PageTable<A>, // PageTable needs to be Debug
     #[allow(dead_code)] // pools might be used in future
     pools: Vec<MemoryPool>,
+    tracker: Arc<AllocationTracker>,
     _phantom: PhantomData<A>,
 }
 
+impl<A: Architecture> MemoryManager<A> {
+    /// Shares `tracker` with this manager so per-dot allocations are reported there
+    pub fn with_tracker(mut self, tracker: Arc<AllocationTracker>) -> Self {
+        self.tracker = tracker;
+        self
+    }
+
+    pub fn tracker(&self) -> &Arc<AllocationTracker> {
+        &self.tracker
+    }
+
+    /// Allocates memory owned by `dot_id` and records it with the allocation tracker
+    pub fn allocate_for_dot(&mut self, dot_id: &str, size: usize) -> Result<MemoryHandle, MemoryError> {
+        let handle = MemoryManagement::allocate(self, size)?;
+        self.tracker.record_allocation(dot_id, handle, size);
+        Ok(handle)
+    }
+
+    /// Returns the dot that owns `handle`, if it was allocated through `allocate_for_dot`
+    pub fn owner_of(&self, handle: MemoryHandle) -> Option<String> {
+        self.tracker.owner(handle)
+    }
+}
+
 /// Core memory management trait
 pub trait MemoryManagement: Sized {
     type Error;
@@ -260,6 +288,7 @@ impl<A: Architecture> MemoryManagement for MemoryManager<A> {
             allocator: Allocator::new(memory_size),
             page_table: PageTable::new(),
             pools: Vec::new(),
+            tracker: Arc::new(AllocationTracker::default()),
             _phantom: PhantomData,
         })
     }
@@ -291,6 +320,7 @@ impl<A: Architecture> MemoryManagement for MemoryManager<A> {
 
         // Report error from Allocator directly
         self.allocator.deallocate(handle)?;
+        self.tracker.record_deallocation(handle);
         Ok(())
     }
 
@@ -396,6 +426,7 @@ mod memory_tests {
             allocator: Allocator::new(test_memory_size),
             page_table: PageTable::new(),
             pools: Vec::new(),
+            tracker: Arc::new(AllocationTracker::default()),
             _phantom: PhantomData,
         }
     }
@@ -627,6 +658,23 @@ mod memory_tests {
             assert!(matches!(result, Err(MemoryError::InvalidAlignment(_))));
         }
     }
+    mod tracking_tests {
+        use super::*;
+
+        #[test]
+        fn test_allocate_for_dot_tags_handle() {
+            let mut mm = create_memory_manager::<Arch64>();
+            let handle = mm.allocate_for_dot("dot-a", 1024).expect("Failed to allocate memory");
+
+            assert_eq!(mm.owner_of(handle).as_deref(), Some("dot-a"));
+            assert_eq!(mm.tracker().dot_usage("dot-a").unwrap().live_bytes, 1024);
+
+            mm.deallocate(handle).expect("Failed to deallocate memory");
+            assert!(mm.owner_of(handle).is_none());
+            assert_eq!(mm.tracker().dot_usage("dot-a").unwrap().live_allocations, 0);
+        }
+    }
+
     #[cfg(test)]
     pub mod memory_isolation_tests {
         use super::*;
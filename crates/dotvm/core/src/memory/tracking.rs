@@ -0,0 +1,292 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Per-dot allocation tracking and leak detection
+//!
+//! The tracker records which dot owns each live `MemoryHandle`, keeps
+//! per-dot live counts and byte totals, and reports allocations that have
+//! outlived a configurable age. Tracking and backtrace capture can both be
+//! switched on and off at runtime.
+
+use super::MemoryHandle;
+use parking_lot::RwLock;
+use std::backtrace::Backtrace;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Default age after which a live allocation is reported as a leak suspect
+pub const DEFAULT_LEAK_AGE: Duration = Duration::from_secs(3600);
+
+/// Runtime-adjustable tracking settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrackingConfig {
+    /// Record allocations at all
+    pub enabled: bool,
+    /// Capture a backtrace for every allocation (debug mode)
+    pub capture_backtraces: bool,
+    /// Allocations older than this are listed in leak reports
+    pub leak_age: Duration,
+}
+
+impl Default for TrackingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            capture_backtraces: false,
+            leak_age: DEFAULT_LEAK_AGE,
+        }
+    }
+}
+
+/// A single tracked allocation
+#[derive(Debug)]
+struct AllocationRecord {
+    dot_id: String,
+    size: usize,
+    allocated_at: Instant,
+    site: Option<String>,
+}
+
+/// Live allocation totals for one dot
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DotMemoryUsage {
+    pub dot_id: String,
+    pub live_allocations: usize,
+    pub live_bytes: usize,
+    /// Allocations made over the dot's lifetime, including freed ones
+    pub total_allocations: u64,
+}
+
+/// An allocation that has outlived the configured leak age
+#[derive(Debug, Clone)]
+pub struct LeakEntry {
+    pub dot_id: String,
+    pub handle: MemoryHandle,
+    pub size: usize,
+    pub age: Duration,
+    /// Allocation site, present only when backtraces were enabled at allocation time
+    pub site: Option<String>,
+}
+
+/// Leak detection report
+#[derive(Debug, Clone)]
+pub struct LeakReport {
+    pub leak_age: Duration,
+    /// Suspects ordered oldest first
+    pub entries: Vec<LeakEntry>,
+}
+
+impl LeakReport {
+    pub fn total_bytes(&self) -> usize {
+        self.entries.iter().map(|e| e.size).sum()
+    }
+}
+
+/// Tracks live allocations per owning dot
+#[derive(Debug)]
+pub struct AllocationTracker {
+    enabled: AtomicBool,
+    capture_backtraces: AtomicBool,
+    leak_age_ms: AtomicU64,
+    records: RwLock<HashMap<MemoryHandle, AllocationRecord>>,
+    usage: RwLock<HashMap<String, DotMemoryUsage>>,
+}
+
+impl Default for AllocationTracker {
+    fn default() -> Self {
+        Self::new(TrackingConfig::default())
+    }
+}
+
+impl AllocationTracker {
+    pub fn new(config: TrackingConfig) -> Self {
+        Self {
+            enabled: AtomicBool::new(config.enabled),
+            capture_backtraces: AtomicBool::new(config.capture_backtraces),
+            leak_age_ms: AtomicU64::new(config.leak_age.as_millis() as u64),
+            records: RwLock::new(HashMap::new()),
+            usage: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> TrackingConfig {
+        TrackingConfig {
+            enabled: self.enabled.load(Ordering::Relaxed),
+            capture_backtraces: self.capture_backtraces.load(Ordering::Relaxed),
+            leak_age: Duration::from_millis(self.leak_age_ms.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Applies new settings. Disabling tracking drops all records so stale
+    /// entries cannot show up as leaks once tracking is re-enabled.
+    pub fn configure(&self, config: TrackingConfig) {
+        let was_enabled = self.enabled.swap(config.enabled, Ordering::Relaxed);
+        self.capture_backtraces.store(config.capture_backtraces, Ordering::Relaxed);
+        self.leak_age_ms.store(config.leak_age.as_millis() as u64, Ordering::Relaxed);
+
+        if was_enabled && !config.enabled {
+            self.records.write().clear();
+            self.usage.write().clear();
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Records a new allocation owned by `dot_id`
+    pub fn record_allocation(&self, dot_id: &str, handle: MemoryHandle, size: usize) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let site = self.capture_backtraces.load(Ordering::Relaxed).then(|| Backtrace::force_capture().to_string());
+
+        let previous = self.records.write().insert(
+            handle,
+            AllocationRecord {
+                dot_id: dot_id.to_string(),
+                size,
+                allocated_at: Instant::now(),
+                site,
+            },
+        );
+
+        let mut usage = self.usage.write();
+        // A handle reused without a recorded free means the free was missed; drop the stale record
+        if let Some(stale) = previous {
+            Self::release(&mut usage, &stale);
+        }
+        let entry = usage.entry(dot_id.to_string()).or_insert_with(|| DotMemoryUsage {
+            dot_id: dot_id.to_string(),
+            ..Default::default()
+        });
+        entry.live_allocations += 1;
+        entry.live_bytes += size;
+        entry.total_allocations += 1;
+    }
+
+    /// Records that `handle` was freed; unknown handles are ignored
+    pub fn record_deallocation(&self, handle: MemoryHandle) {
+        if let Some(record) = self.records.write().remove(&handle) {
+            Self::release(&mut self.usage.write(), &record);
+        }
+    }
+
+    fn release(usage: &mut HashMap<String, DotMemoryUsage>, record: &AllocationRecord) {
+        if let Some(entry) = usage.get_mut(&record.dot_id) {
+            entry.live_allocations = entry.live_allocations.saturating_sub(1);
+            entry.live_bytes = entry.live_bytes.saturating_sub(record.size);
+        }
+    }
+
+    /// Returns the dot that owns `handle`, if it is tracked
+    pub fn owner(&self, handle: MemoryHandle) -> Option<String> {
+        self.records.read().get(&handle).map(|r| r.dot_id.clone())
+    }
+
+    pub fn dot_usage(&self, dot_id: &str) -> Option<DotMemoryUsage> {
+        self.usage.read().get(dot_id).cloned()
+    }
+
+    /// Usage for every dot seen since tracking was enabled, sorted by dot id
+    pub fn usage(&self) -> Vec<DotMemoryUsage> {
+        let mut usage: Vec<_> = self.usage.read().values().cloned().collect();
+        usage.sort_by(|a, b| a.dot_id.cmp(&b.dot_id));
+        usage
+    }
+
+    /// Lists live allocations older than the configured leak age
+    pub fn leak_report(&self) -> LeakReport {
+        self.leak_report_older_than(self.config().leak_age)
+    }
+
+    /// Lists live allocations older than `min_age`
+    pub fn leak_report_older_than(&self, min_age: Duration) -> LeakReport {
+        let now = Instant::now();
+        let mut entries: Vec<LeakEntry> = self
+            .records
+            .read()
+            .iter()
+            .filter_map(|(handle, record)| {
+                let age = now.saturating_duration_since(record.allocated_at);
+                (age >= min_age).then(|| LeakEntry {
+                    dot_id: record.dot_id.clone(),
+                    handle: *handle,
+                    size: record.size,
+                    age,
+                    site: record.site.clone(),
+                })
+            })
+            .collect();
+        entries.sort_by(|a, b| b.age.cmp(&a.age));
+
+        LeakReport { leak_age: min_age, entries }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_per_dot_usage() {
+        let tracker = AllocationTracker::default();
+        tracker.record_allocation("dot-a", MemoryHandle(0), 64);
+        tracker.record_allocation("dot-a", MemoryHandle(64), 128);
+        tracker.record_allocation("dot-b", MemoryHandle(192), 32);
+
+        let a = tracker.dot_usage("dot-a").unwrap();
+        assert_eq!(a.live_allocations, 2);
+        assert_eq!(a.live_bytes, 192);
+
+        tracker.record_deallocation(MemoryHandle(0));
+        let a = tracker.dot_usage("dot-a").unwrap();
+        assert_eq!(a.live_allocations, 1);
+        assert_eq!(a.live_bytes, 128);
+        assert_eq!(a.total_allocations, 2);
+        assert_eq!(tracker.owner(MemoryHandle(192)).as_deref(), Some("dot-b"));
+    }
+
+    #[test]
+    fn test_leak_report_respects_age() {
+        let tracker = AllocationTracker::default();
+        tracker.record_allocation("dot-a", MemoryHandle(0), 64);
+
+        assert!(tracker.leak_report().entries.is_empty());
+
+        let report = tracker.leak_report_older_than(Duration::ZERO);
+        assert_eq!(report.entries.len(), 1);
+        assert_eq!(report.total_bytes(), 64);
+        assert!(report.entries[0].site.is_none());
+    }
+
+    #[test]
+    fn test_backtrace_capture_and_disable() {
+        let tracker = AllocationTracker::new(TrackingConfig {
+            capture_backtraces: true,
+            ..Default::default()
+        });
+        tracker.record_allocation("dot-a", MemoryHandle(0), 64);
+        assert!(tracker.leak_report_older_than(Duration::ZERO).entries[0].site.is_some());
+
+        tracker.configure(TrackingConfig { enabled: false, ..tracker.config() });
+        tracker.record_allocation("dot-a", MemoryHandle(64), 64);
+        assert!(tracker.usage().is_empty());
+        assert!(tracker.leak_report_older_than(Duration::ZERO).entries.is_empty());
+    }
+}
@@ -48,6 +48,22 @@ pub enum DatabaseOpcode {
     /// Stack: [stream_spec_json] -> [stream_result_json]
     DbStream = 0x35,
 
+    /// Read the document a dot stored under a key
+    /// Stack: [collection_name, key] -> [document_json] or [null]
+    DbKeyGet = 0x36,
+
+    /// Store a document under a key, replacing any existing one
+    /// Stack: [collection_name, key, document_json] -> []
+    DbKeyPut = 0x37,
+
+    /// Delete the document stored under a key
+    /// Stack: [collection_name, key] -> [deleted]
+    DbKeyDelete = 0x38,
+
+    /// Find documents whose field equals a value
+    /// Stack: [collection_name, field, value_json] -> [matches_json]
+    DbFind = 0x39,
+
     // Legacy opcodes for backward compatibility
     /// Get a document from the database (legacy)
     /// Stack: [collection_name, document_id] -> [document_json]
@@ -89,6 +105,10 @@ impl DatabaseOpcode {
             "DB_TRANSACTION" | "DBTRANSACTION" => Some(Self::DbTransaction),
             "DB_INDEX" | "DBINDEX" => Some(Self::DbIndex),
             "DB_STREAM" | "DBSTREAM" => Some(Self::DbStream),
+            "DB_KEY_GET" | "DBKEYGET" => Some(Self::DbKeyGet),
+            "DB_KEY_PUT" | "DBKEYPUT" => Some(Self::DbKeyPut),
+            "DB_KEY_DELETE" | "DBKEYDELETE" => Some(Self::DbKeyDelete),
+            "DB_FIND" | "DBFIND" => Some(Self::DbFind),
             // Legacy document opcodes
             "DB_GET" | "DBGET" => Some(Self::DbGet),
             "DB_PUT" | "DBPUT" => Some(Self::DbPut),
@@ -111,6 +131,10 @@ impl DatabaseOpcode {
             DatabaseOpcode::DbTransaction => "DB_TRANSACTION",
             DatabaseOpcode::DbIndex => "DB_INDEX",
             DatabaseOpcode::DbStream => "DB_STREAM",
+            DatabaseOpcode::DbKeyGet => "DB_KEY_GET",
+            DatabaseOpcode::DbKeyPut => "DB_KEY_PUT",
+            DatabaseOpcode::DbKeyDelete => "DB_KEY_DELETE",
+            DatabaseOpcode::DbFind => "DB_FIND",
             // Legacy document opcodes
             DatabaseOpcode::DbGet => "DB_GET",
             DatabaseOpcode::DbPut => "DB_PUT",
@@ -137,6 +161,10 @@ impl DatabaseOpcode {
             0x33 => Some(Self::DbTransaction),
             0x34 => Some(Self::DbIndex),
             0x35 => Some(Self::DbStream),
+            0x36 => Some(Self::DbKeyGet),
+            0x37 => Some(Self::DbKeyPut),
+            0x38 => Some(Self::DbKeyDelete),
+            0x39 => Some(Self::DbFind),
             // Legacy document opcodes
             0x40 => Some(Self::DbGet),
             0x41 => Some(Self::DbPut),
@@ -240,6 +268,14 @@ mod tests {
         assert_eq!(DatabaseOpcode::from_u8(0xFF), None);
     }
 
+    #[test]
+    fn test_keyed_opcodes_round_trip() {
+        for opcode in [DatabaseOpcode::DbKeyGet, DatabaseOpcode::DbKeyPut, DatabaseOpcode::DbKeyDelete, DatabaseOpcode::DbFind] {
+            assert_eq!(DatabaseOpcode::from_u8(opcode.as_u8()), Some(opcode));
+            assert_eq!(DatabaseOpcode::from_mnemonic(opcode.to_mnemonic()), Some(opcode));
+        }
+    }
+
     #[test]
     fn test_display() {
         assert_eq!(DatabaseOpcode::DbRead.to_string(), "DB_READ");
@@ -28,3 +28,4 @@ pub mod stack_opcodes;
 pub mod state_opcodes;
 pub mod system_call_opcodes;
 pub mod vector_opcodes;
+pub mod wide_arithmetic_opcodes;
@@ -0,0 +1,170 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Wide-register arithmetic opcodes
+//!
+//! Arithmetic, comparison and shift opcodes work on values as wide as the
+//! bytecode's architecture word and wrap at that width. `Int64` operands are
+//! sign-extended to the word; wide operands of another width must be converted
+//! first with `WZEXT`, `WSEXT` or `WTRUNC`, whose one operand byte is the target
+//! architecture (`VmArchitecture` as u8, Arch64 or wider):
+//!
+//! - `WZEXT` widens, filling the new upper bits with zeros
+//! - `WSEXT` widens, filling the new upper bits with the sign bit
+//! - `WTRUNC` narrows, keeping the low bits
+//!
+//! A conversion to Arch64 produces an `Int64`.
+
+use std::fmt;
+
+/// Enum representing the wide arithmetic opcodes.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum WideArithmeticOpcode {
+    Add = 0x70,
+    Subtract = 0x71,
+    Multiply = 0x72,
+    DivideUnsigned = 0x73,
+    DivideSigned = 0x74,
+    ModulusUnsigned = 0x75,
+    ModulusSigned = 0x76,
+    /// Pushes -1, 0 or 1 as `Int64`
+    CompareUnsigned = 0x77,
+    /// Pushes -1, 0 or 1 as `Int64`
+    CompareSigned = 0x78,
+    /// Shift amount is an `Int64` on top of the value
+    ShiftLeft = 0x79,
+    ShiftRightUnsigned = 0x7A,
+    ShiftRightSigned = 0x7B,
+    ZeroExtend = 0x7C,
+    SignExtend = 0x7D,
+    Truncate = 0x7E,
+}
+
+impl WideArithmeticOpcode {
+    /// Converts a mnemonic to a `WideArithmeticOpcode`.
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Self> {
+        match mnemonic.to_uppercase().as_str() {
+            "WADD" => Some(Self::Add),
+            "WSUB" => Some(Self::Subtract),
+            "WMUL" => Some(Self::Multiply),
+            "WDIVU" => Some(Self::DivideUnsigned),
+            "WDIVS" => Some(Self::DivideSigned),
+            "WMODU" => Some(Self::ModulusUnsigned),
+            "WMODS" => Some(Self::ModulusSigned),
+            "WCMPU" => Some(Self::CompareUnsigned),
+            "WCMPS" => Some(Self::CompareSigned),
+            "WSHL" => Some(Self::ShiftLeft),
+            "WSHRU" => Some(Self::ShiftRightUnsigned),
+            "WSHRS" => Some(Self::ShiftRightSigned),
+            "WZEXT" => Some(Self::ZeroExtend),
+            "WSEXT" => Some(Self::SignExtend),
+            "WTRUNC" => Some(Self::Truncate),
+            _ => None,
+        }
+    }
+
+    /// Converts a `WideArithmeticOpcode` to its mnemonic.
+    pub fn to_mnemonic(&self) -> &'static str {
+        match self {
+            Self::Add => "WADD",
+            Self::Subtract => "WSUB",
+            Self::Multiply => "WMUL",
+            Self::DivideUnsigned => "WDIVU",
+            Self::DivideSigned => "WDIVS",
+            Self::ModulusUnsigned => "WMODU",
+            Self::ModulusSigned => "WMODS",
+            Self::CompareUnsigned => "WCMPU",
+            Self::CompareSigned => "WCMPS",
+            Self::ShiftLeft => "WSHL",
+            Self::ShiftRightUnsigned => "WSHRU",
+            Self::ShiftRightSigned => "WSHRS",
+            Self::ZeroExtend => "WZEXT",
+            Self::SignExtend => "WSEXT",
+            Self::Truncate => "WTRUNC",
+        }
+    }
+
+    /// Returns the opcode's numerical value.
+    pub fn as_u8(&self) -> u8 {
+        *self as u8
+    }
+
+    /// Converts a numerical value back to a WideArithmeticOpcode.
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0x70 => Some(Self::Add),
+            0x71 => Some(Self::Subtract),
+            0x72 => Some(Self::Multiply),
+            0x73 => Some(Self::DivideUnsigned),
+            0x74 => Some(Self::DivideSigned),
+            0x75 => Some(Self::ModulusUnsigned),
+            0x76 => Some(Self::ModulusSigned),
+            0x77 => Some(Self::CompareUnsigned),
+            0x78 => Some(Self::CompareSigned),
+            0x79 => Some(Self::ShiftLeft),
+            0x7A => Some(Self::ShiftRightUnsigned),
+            0x7B => Some(Self::ShiftRightSigned),
+            0x7C => Some(Self::ZeroExtend),
+            0x7D => Some(Self::SignExtend),
+            0x7E => Some(Self::Truncate),
+            _ => None,
+        }
+    }
+
+    /// Get the number of operand bytes this opcode expects
+    pub fn operand_size(&self) -> usize {
+        match self {
+            Self::ZeroExtend | Self::SignExtend | Self::Truncate => 1, // u8 target architecture
+            _ => 0,
+        }
+    }
+
+    /// Whether this opcode converts between widths rather than computing at the architecture width
+    pub fn is_conversion(&self) -> bool {
+        self.operand_size() > 0
+    }
+}
+
+impl fmt::Display for WideArithmeticOpcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_mnemonic())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trips() {
+        for value in 0x70..=0x7E {
+            let opcode = WideArithmeticOpcode::from_u8(value).unwrap();
+            assert_eq!(opcode.as_u8(), value);
+            assert_eq!(WideArithmeticOpcode::from_mnemonic(opcode.to_mnemonic()), Some(opcode));
+        }
+        assert_eq!(WideArithmeticOpcode::from_u8(0x7F), None);
+        assert_eq!(WideArithmeticOpcode::from_mnemonic("wmul"), Some(WideArithmeticOpcode::Multiply));
+    }
+
+    #[test]
+    fn test_operand_sizes() {
+        assert_eq!(WideArithmeticOpcode::Add.operand_size(), 0);
+        assert_eq!(WideArithmeticOpcode::ShiftRightSigned.operand_size(), 0);
+        assert_eq!(WideArithmeticOpcode::ZeroExtend.operand_size(), 1);
+        assert_eq!(WideArithmeticOpcode::SignExtend.operand_size(), 1);
+        assert_eq!(WideArithmeticOpcode::Truncate.operand_size(), 1);
+    }
+}
@@ -296,7 +296,8 @@ impl DatabaseOpcodeExecutor {
         let db_interface: Arc<dyn DatabaseInterface> = Arc::new(database);
 
         // Create document storage using the database interface
-        let document_storage: Arc<dyn DocumentStorage> = Arc::new(dotdb_core::document::storage::DocumentStore::new(db_interface.clone()));
+        let document_store = dotdb_core::document::storage::DocumentStore::open(db_interface.clone()).map_err(|e| DatabaseError::Storage(StorageError::NotFound(format!("Document store creation error: {}", e))))?;
+        let document_storage: Arc<dyn DocumentStorage> = Arc::new(document_store);
 
         // Create collection manager
         let collection_manager = Arc::new(CollectionManager::new(document_storage.clone()));
@@ -0,0 +1,519 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Dot-scoped database access
+//!
+//! Backs the `db_get`, `db_put`, `db_delete` and `db_find` host functions a dot
+//! imports from the `dotlanth` module. Calls go through a collection manager confined
+//! to the dot's own namespace, are checked against the collections the dot declared
+//! in its ABI, and are metered against the execution's budget.
+//!
+//! Documents are addressed by key: each key maps to a fixed document id within its
+//! collection, so writing an existing key replaces the document. The key is stored in
+//! the document's reserved `_key` field.
+
+use crate::opcode::db_opcodes::DatabaseOpcode;
+use dotdb_core::document::{CollectionManager, DocumentError, DocumentId, Namespace};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Import module the database host functions are exposed under
+pub const DB_HOST_MODULE: &str = "dotlanth";
+
+/// Document field holding the key a document was written under
+pub const KEY_FIELD: &str = "_key";
+
+/// Database host functions a dot can import
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DbHostFunction {
+    Get,
+    Put,
+    Delete,
+    Find,
+}
+
+impl DbHostFunction {
+    /// Resolve an import of `module.name`
+    pub fn from_import(module: &str, name: &str) -> Option<Self> {
+        if module != DB_HOST_MODULE {
+            return None;
+        }
+        match name {
+            "db_get" => Some(Self::Get),
+            "db_put" => Some(Self::Put),
+            "db_delete" => Some(Self::Delete),
+            "db_find" => Some(Self::Find),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Get => "db_get",
+            Self::Put => "db_put",
+            Self::Delete => "db_delete",
+            Self::Find => "db_find",
+        }
+    }
+
+    /// Opcode a call to this function is transpiled to
+    pub fn opcode(&self) -> DatabaseOpcode {
+        match self {
+            Self::Get => DatabaseOpcode::DbKeyGet,
+            Self::Put => DatabaseOpcode::DbKeyPut,
+            Self::Delete => DatabaseOpcode::DbKeyDelete,
+            Self::Find => DatabaseOpcode::DbFind,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DbAccess {
+    Read,
+    Write,
+}
+
+impl fmt::Display for DbAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbAccess::Read => write!(f, "read"),
+            DbAccess::Write => write!(f, "write"),
+        }
+    }
+}
+
+/// Collections a dot declared in its ABI and what it may do with each
+///
+/// Anything not declared is denied, and a write grant does not imply a read grant.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DbAccessPolicy {
+    grants: HashMap<String, CollectionGrant>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct CollectionGrant {
+    read: bool,
+    write: bool,
+}
+
+impl DbAccessPolicy {
+    /// A policy that denies everything
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow reads and/or writes of `collection`, adding to any earlier grant
+    pub fn grant(mut self, collection: impl Into<String>, read: bool, write: bool) -> Self {
+        let grant = self.grants.entry(collection.into()).or_default();
+        grant.read |= read;
+        grant.write |= write;
+        self
+    }
+
+    pub fn allows(&self, collection: &str, access: DbAccess) -> bool {
+        self.grants.get(collection).is_some_and(|grant| match access {
+            DbAccess::Read => grant.read,
+            DbAccess::Write => grant.write,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.grants.is_empty()
+    }
+}
+
+/// Cost of each database call, charged against the execution's budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DbCostModel {
+    pub get: u64,
+    pub put: u64,
+    pub delete: u64,
+    pub find: u64,
+    /// Charged per started KiB of document read or written
+    pub per_kib: u64,
+    /// Charged per document a find returns
+    pub per_result: u64,
+}
+
+impl Default for DbCostModel {
+    fn default() -> Self {
+        Self {
+            get: 10,
+            put: 25,
+            delete: 15,
+            find: 50,
+            per_kib: 1,
+            per_result: 2,
+        }
+    }
+}
+
+/// Database calls made by one execution
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DbCallMetrics {
+    pub gets: u64,
+    pub puts: u64,
+    pub deletes: u64,
+    pub finds: u64,
+    /// Calls refused by the access policy
+    pub denied: u64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    /// Total charged under the cost model
+    pub cost: u64,
+}
+
+impl DbCallMetrics {
+    pub fn reads(&self) -> u64 {
+        self.gets + self.finds
+    }
+
+    pub fn writes(&self) -> u64 {
+        self.puts + self.deletes
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DotDatabaseError {
+    #[error("Dot {dot_id} did not declare {access} access to collection '{collection}'")]
+    AccessDenied { dot_id: String, collection: String, access: DbAccess },
+
+    #[error("Database budget exhausted: {used} used of {limit}")]
+    BudgetExhausted { used: u64, limit: u64 },
+
+    #[error("Dot {0} is already executing")]
+    Reentrant(String),
+
+    #[error("Invalid value: {0}")]
+    InvalidValue(String),
+
+    #[error("Document error: {0}")]
+    Document(#[from] DocumentError),
+}
+
+pub type DotDatabaseResult<T> = Result<T, DotDatabaseError>;
+
+/// Dots with an execution in progress
+///
+/// Share one set between every executor that can be started by a database write,
+/// such as a trigger, so a write that would run the dot that made it again is
+/// refused instead of recursing.
+#[derive(Debug, Clone, Default)]
+pub struct ActiveDots(Arc<Mutex<HashSet<String>>>);
+
+impl ActiveDots {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark `dot_id` as executing until the returned guard is dropped
+    pub fn enter(&self, dot_id: &str) -> DotDatabaseResult<ActiveDotGuard> {
+        if !self.0.lock().unwrap().insert(dot_id.to_string()) {
+            return Err(DotDatabaseError::Reentrant(dot_id.to_string()));
+        }
+        Ok(ActiveDotGuard {
+            dots: self.0.clone(),
+            dot_id: dot_id.to_string(),
+        })
+    }
+
+    pub fn is_active(&self, dot_id: &str) -> bool {
+        self.0.lock().unwrap().contains(dot_id)
+    }
+}
+
+/// Marks a dot as executing; see [`ActiveDots::enter`]
+#[derive(Debug)]
+pub struct ActiveDotGuard {
+    dots: Arc<Mutex<HashSet<String>>>,
+    dot_id: String,
+}
+
+impl Drop for ActiveDotGuard {
+    fn drop(&mut self) {
+        self.dots.lock().unwrap().remove(&self.dot_id);
+    }
+}
+
+/// Namespace a dot's collections live in
+///
+/// Dot ids that are not valid namespace names are replaced by a digest of the id.
+pub fn dot_namespace(dot_id: &str) -> String {
+    let namespace = format!("dot-{}", dot_id);
+    if Namespace::new(namespace.as_str()).is_ok() {
+        return namespace;
+    }
+    let digest = Sha256::digest(dot_id.as_bytes());
+    format!("dot-{}", hex::encode(&digest[..16]))
+}
+
+/// Fixed id of the document stored under `key`
+fn key_document_id(key: &str) -> DocumentId {
+    let digest = Sha256::digest(key.as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    DocumentId::from_uuid(uuid::Builder::from_custom_bytes(bytes).into_uuid())
+}
+
+/// Database access for one execution of a dot
+///
+/// Holds the dot's place in [`ActiveDots`] for as long as it lives.
+pub struct DotDatabase {
+    dot_id: String,
+    manager: CollectionManager,
+    policy: DbAccessPolicy,
+    costs: DbCostModel,
+    budget: Option<u64>,
+    metrics: DbCallMetrics,
+    _active: ActiveDotGuard,
+}
+
+impl fmt::Debug for DotDatabase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DotDatabase")
+            .field("dot_id", &self.dot_id)
+            .field("namespace", &self.manager.namespace())
+            .field("policy", &self.policy)
+            .field("budget", &self.budget)
+            .field("metrics", &self.metrics)
+            .finish()
+    }
+}
+
+impl DotDatabase {
+    /// Open `dot_id`'s namespace of `manager` for one execution
+    ///
+    /// Fails with [`DotDatabaseError::Reentrant`] if the dot is already executing.
+    pub fn open(manager: &CollectionManager, dot_id: &str, policy: DbAccessPolicy, active: &ActiveDots) -> DotDatabaseResult<Self> {
+        let manager = manager.with_namespace(&dot_namespace(dot_id))?;
+        let active = active.enter(dot_id)?;
+        Ok(Self {
+            dot_id: dot_id.to_string(),
+            manager,
+            policy,
+            costs: DbCostModel::default(),
+            budget: None,
+            metrics: DbCallMetrics::default(),
+            _active: active,
+        })
+    }
+
+    pub fn with_costs(mut self, costs: DbCostModel) -> Self {
+        self.costs = costs;
+        self
+    }
+
+    /// Trap once the calls have cost more than `budget`
+    pub fn with_budget(mut self, budget: u64) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    pub fn dot_id(&self) -> &str {
+        &self.dot_id
+    }
+
+    pub fn namespace(&self) -> &str {
+        self.manager.namespace()
+    }
+
+    pub fn metrics(&self) -> &DbCallMetrics {
+        &self.metrics
+    }
+
+    /// Read the document stored under `key`
+    pub fn get(&mut self, collection: &str, key: &str) -> DotDatabaseResult<Option<Value>> {
+        self.authorize(collection, DbAccess::Read)?;
+        self.metrics.gets += 1;
+        self.charge(self.costs.get)?;
+
+        let Some(mut document) = self.manager.get_value(collection, &key_document_id(key))? else {
+            return Ok(None);
+        };
+        let size = encoded_len(&document);
+        self.metrics.bytes_read += size;
+        self.charge(self.costs.per_kib * size.div_ceil(1024))?;
+
+        if let Value::Object(fields) = &mut document {
+            fields.remove(KEY_FIELD);
+        }
+        Ok(Some(document))
+    }
+
+    /// Store `value`, which must be a JSON object, under `key`
+    pub fn put(&mut self, collection: &str, key: &str, value: Value) -> DotDatabaseResult<()> {
+        self.authorize(collection, DbAccess::Write)?;
+        let Value::Object(mut fields) = value else {
+            return Err(DotDatabaseError::InvalidValue(format!("db_put expects a JSON object, got {}", value)));
+        };
+        fields.insert(KEY_FIELD.to_string(), Value::String(key.to_string()));
+        let document = Value::Object(fields);
+
+        let size = encoded_len(&document);
+        self.metrics.puts += 1;
+        self.charge(self.costs.put + self.costs.per_kib * size.div_ceil(1024))?;
+
+        self.manager.put_value(collection, &key_document_id(key), document)?;
+        self.metrics.bytes_written += size;
+        Ok(())
+    }
+
+    /// Delete the document stored under `key`, returning whether there was one
+    pub fn delete(&mut self, collection: &str, key: &str) -> DotDatabaseResult<bool> {
+        self.authorize(collection, DbAccess::Write)?;
+        self.metrics.deletes += 1;
+        self.charge(self.costs.delete)?;
+        Ok(self.manager.delete(collection, &key_document_id(key))?)
+    }
+
+    /// Documents whose top-level `field` equals `value`, as `{"key", "value"}` objects
+    pub fn find(&mut self, collection: &str, field: &str, value: &Value) -> DotDatabaseResult<Vec<Value>> {
+        self.authorize(collection, DbAccess::Read)?;
+        self.metrics.finds += 1;
+        self.charge(self.costs.find)?;
+
+        let matches = self.manager.find_by_field(collection, field, value)?;
+        let size: u64 = matches.iter().map(|(_, document)| encoded_len(document)).sum();
+        self.metrics.bytes_read += size;
+        self.charge(self.costs.per_result * matches.len() as u64 + self.costs.per_kib * size.div_ceil(1024))?;
+
+        Ok(matches
+            .into_iter()
+            .map(|(_, mut document)| {
+                let key = match &mut document {
+                    Value::Object(fields) => fields.remove(KEY_FIELD).unwrap_or(Value::Null),
+                    _ => Value::Null,
+                };
+                serde_json::json!({ "key": key, "value": document })
+            })
+            .collect())
+    }
+
+    fn authorize(&mut self, collection: &str, access: DbAccess) -> DotDatabaseResult<()> {
+        if self.policy.allows(collection, access) {
+            return Ok(());
+        }
+        self.metrics.denied += 1;
+        Err(DotDatabaseError::AccessDenied {
+            dot_id: self.dot_id.clone(),
+            collection: collection.to_string(),
+            access,
+        })
+    }
+
+    fn charge(&mut self, cost: u64) -> DotDatabaseResult<()> {
+        self.metrics.cost += cost;
+        match self.budget {
+            Some(limit) if self.metrics.cost > limit => Err(DotDatabaseError::BudgetExhausted { used: self.metrics.cost, limit }),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn encoded_len(value: &Value) -> u64 {
+    serde_json::to_vec(value).map(|bytes| bytes.len() as u64).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dotdb_core::document::create_in_memory_collection_manager;
+    use serde_json::json;
+
+    fn open(manager: &CollectionManager, policy: DbAccessPolicy) -> DotDatabase {
+        DotDatabase::open(manager, "order-processor", policy, &ActiveDots::new()).unwrap()
+    }
+
+    #[test]
+    fn test_put_get_delete_by_key() {
+        let manager = create_in_memory_collection_manager().unwrap();
+        let mut db = open(&manager, DbAccessPolicy::new().grant("orders", true, true));
+
+        db.put("orders", "o-1", json!({"status": "open"})).unwrap();
+        db.put("orders", "o-1", json!({"status": "paid"})).unwrap();
+        assert_eq!(db.get("orders", "o-1").unwrap(), Some(json!({"status": "paid"})));
+        assert!(db.delete("orders", "o-1").unwrap());
+        assert_eq!(db.get("orders", "o-1").unwrap(), None);
+
+        let metrics = db.metrics();
+        assert_eq!((metrics.reads(), metrics.writes()), (2, 3));
+        assert!(metrics.cost > 0);
+    }
+
+    #[test]
+    fn test_documents_live_in_the_dot_namespace() {
+        let manager = create_in_memory_collection_manager().unwrap();
+        let mut db = open(&manager, DbAccessPolicy::new().grant("orders", false, true));
+        db.put("orders", "o-1", json!({"total": 5})).unwrap();
+
+        assert_eq!(manager.count("orders").unwrap(), 0);
+        assert_eq!(manager.with_namespace("dot-order-processor").unwrap().count("orders").unwrap(), 1);
+        assert_eq!(dot_namespace("not a valid/namespace").len(), "dot-".len() + 32);
+    }
+
+    #[test]
+    fn test_undeclared_access_is_denied() {
+        let manager = create_in_memory_collection_manager().unwrap();
+        let mut db = open(&manager, DbAccessPolicy::new().grant("orders", true, false));
+
+        let error = db.put("orders", "o-1", json!({})).unwrap_err();
+        assert!(matches!(error, DotDatabaseError::AccessDenied { access: DbAccess::Write, .. }));
+        assert!(matches!(db.get("customers", "c-1"), Err(DotDatabaseError::AccessDenied { .. })));
+        assert_eq!(db.metrics().denied, 2);
+        assert_eq!(db.metrics().cost, 0);
+    }
+
+    #[test]
+    fn test_find_returns_keys() {
+        let manager = create_in_memory_collection_manager().unwrap();
+        let mut db = open(&manager, DbAccessPolicy::new().grant("orders", true, true));
+        db.put("orders", "o-1", json!({"status": "open"})).unwrap();
+        db.put("orders", "o-2", json!({"status": "paid"})).unwrap();
+
+        let found = db.find("orders", "status", &json!("paid")).unwrap();
+        assert_eq!(found, vec![json!({"key": "o-2", "value": {"status": "paid"}})]);
+    }
+
+    #[test]
+    fn test_budget_traps() {
+        let manager = create_in_memory_collection_manager().unwrap();
+        let mut db = open(&manager, DbAccessPolicy::new().grant("orders", true, true)).with_budget(30);
+
+        db.get("orders", "o-1").unwrap();
+        assert!(matches!(db.put("orders", "o-1", json!({})), Err(DotDatabaseError::BudgetExhausted { limit: 30, .. })));
+        assert_eq!(manager.with_namespace("dot-order-processor").unwrap().count("orders").unwrap(), 0);
+    }
+
+    #[test]
+    fn test_reentrant_execution_is_refused() {
+        let manager = create_in_memory_collection_manager().unwrap();
+        let active = ActiveDots::new();
+        let db = DotDatabase::open(&manager, "order-processor", DbAccessPolicy::new(), &active).unwrap();
+
+        assert!(matches!(
+            DotDatabase::open(&manager, "order-processor", DbAccessPolicy::new(), &active),
+            Err(DotDatabaseError::Reentrant(_))
+        ));
+        assert!(DotDatabase::open(&manager, "invoicer", DbAccessPolicy::new(), &active).is_ok());
+
+        drop(db);
+        assert!(!active.is_active("order-processor"));
+    }
+}
@@ -0,0 +1,426 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::{ExecutionError, Task};
+use metrics::{counter, gauge};
+use parking_lot::Mutex;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use tokio::sync::Notify;
+
+/// Virtual time charged to a dot of weight 1 for one task
+const TASK_COST: u64 = 1 << 32;
+
+/// Limits and default weight of the per-dot queues
+#[derive(Debug, Clone, PartialEq)]
+pub struct FairShareConfig {
+    /// Weight of dots without one set through [`FairQueue::set_weight`]
+    pub default_weight: u32,
+    /// Tasks handed to the scheduler and not yet completed, across all dots
+    pub max_in_flight: usize,
+    /// Tasks a single dot may have waiting; more are rejected with `SchedulerOverload`
+    pub max_queue_depth: usize,
+}
+
+impl Default for FairShareConfig {
+    fn default() -> Self {
+        Self {
+            default_weight: 1,
+            max_in_flight: num_cpus::get(),
+            max_queue_depth: 10_000,
+        }
+    }
+}
+
+/// Snapshot of one dot's queue; `dot_id` is `None` for tasks without an owner
+#[derive(Debug, Clone, PartialEq)]
+pub struct DotQueueStatus {
+    pub dot_id: Option<String>,
+    pub weight: u32,
+    pub queued: usize,
+    pub in_flight: usize,
+    pub dispatched: u64,
+    /// Times the dot's next task was passed over by more tasks of other dots than
+    /// [`FairQueue::fairness_bound`] allows
+    pub starved: u64,
+}
+
+/// A waiting task; the heap yields the highest priority first, then the oldest
+struct QueuedTask {
+    task: Task,
+    seq: u64,
+}
+
+impl Ord for QueuedTask {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.task.priority.cmp(&other.task.priority).then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for QueuedTask {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for QueuedTask {
+    fn eq(&self, other: &Self) -> bool {
+        self.seq == other.seq
+    }
+}
+
+impl Eq for QueuedTask {}
+
+#[derive(Default)]
+struct DotQueue {
+    weight: u32,
+    tasks: BinaryHeap<QueuedTask>,
+    /// Virtual finish times and arrival order of the queued tasks, oldest first. A dispatch
+    /// consumes the oldest tag whichever task it takes, so priority reorders tasks within
+    /// the dot's share without enlarging it.
+    finish_tags: VecDeque<(u64, u64)>,
+    last_finish: u64,
+    in_flight: usize,
+    dispatched: u64,
+    /// Tasks of other dots dispatched since the current head tag became the head
+    passed_over: usize,
+    head_starved: bool,
+    starved: u64,
+}
+
+impl DotQueue {
+    fn cost(&self) -> u64 {
+        TASK_COST / u64::from(self.weight.max(1))
+    }
+}
+
+#[derive(Default)]
+struct FairQueueState {
+    queues: HashMap<Option<String>, DotQueue>,
+    weights: HashMap<String, u32>,
+    /// Finish time of the last dispatched task
+    virtual_time: u64,
+    next_seq: u64,
+    /// Owner of every task handed out and not yet completed
+    in_flight: HashMap<u64, Option<String>>,
+}
+
+impl FairQueueState {
+    /// Largest number of other dots' tasks that may be dispatched ahead of `key`'s next task
+    fn fairness_bound(&self, key: &Option<String>) -> usize {
+        let Some(queue) = self.queues.get(key) else {
+            return 0;
+        };
+        self.queues.iter().filter(|(other, _)| *other != key).map(|(_, other)| (queue.cost() / other.cost()) as usize + 1).sum()
+    }
+}
+
+/// Per-dot task queues in front of the [`super::WorkStealingScheduler`] with weighted fair queuing
+///
+/// Each dot has its own queue. A task is stamped with a virtual finish time
+/// `max(V, F_prev) + cost / weight`, where `V` is the finish time of the last dispatched task
+/// and `F_prev` the previous finish time of the same dot, and the dispatcher always takes the
+/// queue with the lowest finish time at its head. Over any busy period a dot therefore gets a
+/// share of dispatches proportional to its weight, however many tasks the others submit.
+///
+/// Once a dot has a task waiting, at most `⌊w_j / w_i⌋ + 1` tasks of every other dot `j` are
+/// dispatched before it ([`Self::fairness_bound`]); a flood from one dot delays another by at most
+/// that many task executions. Within its share a dot's tasks are taken by priority, so a
+/// `TaskPriority::Critical` task overtakes the dot's own queued work but not other dots'.
+pub struct FairQueue {
+    config: FairShareConfig,
+    state: Mutex<FairQueueState>,
+    ready: Notify,
+}
+
+impl Default for FairQueue {
+    fn default() -> Self {
+        Self::new(FairShareConfig::default())
+    }
+}
+
+impl FairQueue {
+    pub fn new(config: FairShareConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(FairQueueState::default()),
+            ready: Notify::new(),
+        }
+    }
+
+    pub fn config(&self) -> &FairShareConfig {
+        &self.config
+    }
+
+    /// Sets a dot's share relative to other dots; a weight of 0 is treated as 1.
+    /// Tasks already queued keep the finish times they were stamped with.
+    pub fn set_weight(&self, dot_id: &str, weight: u32) {
+        let weight = weight.max(1);
+        let mut state = self.state.lock();
+        state.weights.insert(dot_id.to_string(), weight);
+        if let Some(queue) = state.queues.get_mut(&Some(dot_id.to_string())) {
+            queue.weight = weight;
+        }
+    }
+
+    /// Queues a task behind its dot's earlier tasks
+    ///
+    /// # Errors
+    /// `SchedulerOverload` if the dot already has `max_queue_depth` tasks waiting
+    pub fn enqueue(&self, task: Task) -> Result<(), ExecutionError> {
+        let mut state = self.state.lock();
+        let key = task.dot_id.clone();
+        let weight = key.as_ref().and_then(|dot_id| state.weights.get(dot_id).copied()).unwrap_or(self.config.default_weight);
+        let seq = state.next_seq;
+        let virtual_time = state.virtual_time;
+
+        let queue = state.queues.entry(key.clone()).or_insert_with(|| DotQueue { weight, ..Default::default() });
+        if queue.tasks.len() >= self.config.max_queue_depth {
+            return Err(ExecutionError::SchedulerOverload);
+        }
+        if queue.tasks.is_empty() {
+            queue.passed_over = 0;
+            queue.head_starved = false;
+        }
+        let finish = virtual_time.max(queue.last_finish) + queue.cost();
+        queue.last_finish = finish;
+        queue.finish_tags.push_back((finish, seq));
+        queue.tasks.push(QueuedTask { task, seq });
+        record_depth(&key, queue);
+
+        state.next_seq += 1;
+        drop(state);
+        self.ready.notify_waiters();
+        Ok(())
+    }
+
+    /// Takes the next task in fair-share order, or `None` if nothing is queued or
+    /// `max_in_flight` tasks are still running
+    pub fn try_dispatch(&self) -> Option<Task> {
+        let mut state = self.state.lock();
+        if state.in_flight.len() >= self.config.max_in_flight {
+            return None;
+        }
+
+        let key = state.queues.iter().filter_map(|(key, queue)| queue.finish_tags.front().map(|tag| (tag, key))).min()?.1.clone();
+        let queue = state.queues.get_mut(&key)?;
+        let (finish, _) = queue.finish_tags.pop_front()?;
+        let task = queue.tasks.pop()?.task;
+        queue.in_flight += 1;
+        queue.dispatched += 1;
+        queue.passed_over = 0;
+        queue.head_starved = false;
+        record_depth(&key, queue);
+
+        state.virtual_time = finish;
+        state.in_flight.insert(task.id, key.clone());
+
+        // Count the dispatch against every other dot that has a task waiting
+        let bounds: Vec<(Option<String>, usize)> = state
+            .queues
+            .iter()
+            .filter(|(other, queue)| **other != key && !queue.tasks.is_empty())
+            .map(|(other, _)| (other.clone(), state.fairness_bound(other)))
+            .collect();
+        for (other, bound) in bounds {
+            let Some(queue) = state.queues.get_mut(&other) else {
+                continue;
+            };
+            queue.passed_over += 1;
+            if queue.passed_over > bound && !queue.head_starved {
+                queue.head_starved = true;
+                queue.starved += 1;
+                counter!("dotvm_scheduler_starved_total", 1, "dot_id" => dot_label(&other));
+            }
+        }
+
+        Some(task)
+    }
+
+    /// Waits until a task can be dispatched and takes it
+    pub async fn next(&self) -> Task {
+        loop {
+            let notified = self.ready.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if let Some(task) = self.try_dispatch() {
+                return task;
+            }
+            notified.await;
+        }
+    }
+
+    /// Frees the slot of a dispatched task; returns false if the task was not in flight
+    pub fn complete(&self, task_id: u64) -> bool {
+        let mut state = self.state.lock();
+        let Some(key) = state.in_flight.remove(&task_id) else {
+            return false;
+        };
+        if let Some(queue) = state.queues.get_mut(&key) {
+            queue.in_flight = queue.in_flight.saturating_sub(1);
+        }
+        drop(state);
+        self.ready.notify_waiters();
+        true
+    }
+
+    /// Largest number of other dots' tasks that may be dispatched ahead of the dot's next task,
+    /// given the dots known to the queue; `None` selects tasks without an owner
+    pub fn fairness_bound(&self, dot_id: Option<&str>) -> usize {
+        self.state.lock().fairness_bound(&dot_id.map(str::to_string))
+    }
+
+    /// Returns the queue of a dot, if it has ever submitted a task
+    pub fn status(&self, dot_id: Option<&str>) -> Option<DotQueueStatus> {
+        let key = dot_id.map(str::to_string);
+        self.state.lock().queues.get(&key).map(|queue| queue_status(&key, queue))
+    }
+
+    /// Returns the queue of every dot that has submitted a task
+    pub fn statuses(&self) -> Vec<DotQueueStatus> {
+        let state = self.state.lock();
+        let mut statuses: Vec<DotQueueStatus> = state.queues.iter().map(|(key, queue)| queue_status(key, queue)).collect();
+        statuses.sort_by(|a, b| a.dot_id.cmp(&b.dot_id));
+        statuses
+    }
+}
+
+fn queue_status(key: &Option<String>, queue: &DotQueue) -> DotQueueStatus {
+    DotQueueStatus {
+        dot_id: key.clone(),
+        weight: queue.weight,
+        queued: queue.tasks.len(),
+        in_flight: queue.in_flight,
+        dispatched: queue.dispatched,
+        starved: queue.starved,
+    }
+}
+
+fn dot_label(key: &Option<String>) -> String {
+    key.clone().unwrap_or_default()
+}
+
+fn record_depth(key: &Option<String>, queue: &DotQueue) {
+    gauge!("dotvm_scheduler_queue_depth", queue.tasks.len() as f64, "dot_id" => dot_label(key));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::execution_controller::TaskPriority;
+
+    fn task(id: u64, dot_id: &str, priority: TaskPriority) -> Task {
+        Task {
+            id,
+            priority,
+            resource_requirements: Default::default(),
+            dot_id: Some(dot_id.to_string()),
+        }
+    }
+
+    fn queue(max_in_flight: usize) -> FairQueue {
+        FairQueue::new(FairShareConfig {
+            max_in_flight,
+            ..FairShareConfig::default()
+        })
+    }
+
+    /// Dispatches and completes everything queued, returning the owning dots in order
+    fn drain(queue: &FairQueue) -> Vec<String> {
+        let mut order = Vec::new();
+        while let Some(task) = queue.try_dispatch() {
+            queue.complete(task.id);
+            order.push(task.dot_id.unwrap());
+        }
+        order
+    }
+
+    #[test]
+    fn test_dispatch_share_follows_weights() {
+        let queue = queue(1);
+        queue.set_weight("heavy", 3);
+        for id in 0..300 {
+            queue.enqueue(task(id, "flood", TaskPriority::Low)).unwrap();
+        }
+        for id in 300..400 {
+            queue.enqueue(task(id, "heavy", TaskPriority::Low)).unwrap();
+        }
+
+        let order = drain(&queue);
+        let heavy_in_first_100 = order[..100].iter().filter(|dot| *dot == "heavy").count();
+        assert_eq!(heavy_in_first_100, 75);
+        assert_eq!(queue.status(Some("flood")).unwrap().starved, 0);
+        assert_eq!(queue.status(Some("heavy")).unwrap().starved, 0);
+    }
+
+    #[test]
+    fn test_late_dot_waits_at_most_the_fairness_bound() {
+        let queue = queue(1);
+        for id in 0..1000 {
+            queue.enqueue(task(id, "flood", TaskPriority::Low)).unwrap();
+        }
+        for _ in 0..500 {
+            let task = queue.try_dispatch().unwrap();
+            queue.complete(task.id);
+        }
+
+        queue.enqueue(task(5000, "quiet", TaskPriority::Low)).unwrap();
+        let bound = queue.fairness_bound(Some("quiet"));
+        assert_eq!(bound, 2);
+        let position = drain(&queue).iter().position(|dot| dot == "quiet").unwrap();
+        assert!(position <= bound, "quiet dot waited behind {position} tasks");
+    }
+
+    #[test]
+    fn test_critical_task_preempts_only_within_its_dot() {
+        let queue = queue(1);
+        for id in 0..4 {
+            queue.enqueue(task(id, "a", TaskPriority::Low)).unwrap();
+            queue.enqueue(task(10 + id, "b", TaskPriority::Low)).unwrap();
+        }
+        queue.enqueue(task(99, "a", TaskPriority::Critical)).unwrap();
+
+        let mut order = Vec::new();
+        while let Some(task) = queue.try_dispatch() {
+            queue.complete(task.id);
+            order.push(task.id);
+        }
+        // The critical task goes first but uses a's slot, so b keeps alternating with a
+        assert_eq!(order, vec![99, 10, 0, 11, 1, 12, 2, 13, 3]);
+    }
+
+    #[test]
+    fn test_in_flight_limit_and_queue_depth() {
+        let queue = FairQueue::new(FairShareConfig {
+            default_weight: 1,
+            max_in_flight: 2,
+            max_queue_depth: 3,
+        });
+        for id in 0..3 {
+            queue.enqueue(task(id, "a", TaskPriority::Low)).unwrap();
+        }
+        assert!(matches!(queue.enqueue(task(3, "a", TaskPriority::Low)), Err(ExecutionError::SchedulerOverload)));
+
+        let first = queue.try_dispatch().unwrap();
+        queue.try_dispatch().unwrap();
+        assert!(queue.try_dispatch().is_none());
+        assert_eq!(queue.status(Some("a")).unwrap().in_flight, 2);
+
+        assert!(queue.complete(first.id));
+        assert!(!queue.complete(first.id));
+        assert_eq!(queue.try_dispatch().unwrap().id, 2);
+    }
+}
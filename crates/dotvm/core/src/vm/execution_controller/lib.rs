@@ -14,13 +14,18 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-pub use super::{load_balancing::LoadBalancer, priority_execution::PriorityExecutor, resource_allocation::ResourceAllocator, work_stealing_scheduler::WorkStealingScheduler};
+pub use super::{
+    fair_queue::FairQueue, load_balancing::LoadBalancer, priority_execution::PriorityExecutor, quota::QuotaResource, resource_allocation::ResourceAllocator,
+    work_stealing_scheduler::WorkStealingScheduler,
+};
+use std::sync::Arc;
 
 pub struct ExecutionController {
+    fair_queue: Arc<FairQueue>,
     scheduler: WorkStealingScheduler,
     priority_executor: PriorityExecutor,
     load_balancer: LoadBalancer,
-    resource_allocator: ResourceAllocator,
+    resource_allocator: Arc<ResourceAllocator>,
 }
 
 /// Core execution management system coordinating scheduling, prioritization, and resource management.
@@ -28,6 +33,7 @@ pub struct ExecutionController {
 /// - Priority adjustment
 /// - Resource allocation
 /// - Load-balanced distribution
+/// - Per-dot fair queuing
 /// - Work-stealing scheduling
 impl Default for ExecutionController {
     fn default() -> Self {
@@ -39,19 +45,41 @@ impl ExecutionController {
     /// Initializes all subsystems with default configurations:
     /// - Fresh instances of scheduler, executor, load balancer, and resource allocator
     pub fn new() -> Self {
+        Self::with_resource_allocator(Arc::new(ResourceAllocator::new()))
+    }
+
+    /// Builds a controller around an allocator shared with other components,
+    /// e.g. the service that registers per-dot quotas
+    pub fn with_resource_allocator(resource_allocator: Arc<ResourceAllocator>) -> Self {
+        Self::with_shared(resource_allocator, Arc::new(FairQueue::default()))
+    }
+
+    /// Builds a controller around an allocator and fair queue shared with other components,
+    /// e.g. the metrics service that reports per-dot queue depth
+    pub fn with_shared(resource_allocator: Arc<ResourceAllocator>, fair_queue: Arc<FairQueue>) -> Self {
         Self {
+            fair_queue,
             scheduler: WorkStealingScheduler::new(),
             priority_executor: PriorityExecutor::new(),
             load_balancer: LoadBalancer::new(),
-            resource_allocator: ResourceAllocator::new(),
+            resource_allocator,
         }
     }
 
+    pub fn resource_allocator(&self) -> &Arc<ResourceAllocator> {
+        &self.resource_allocator
+    }
+
+    pub fn fair_queue(&self) -> &Arc<FairQueue> {
+        &self.fair_queue
+    }
+
     /// Executes a task through the full processing pipeline:
     /// 1. **Resource Allocation**: Reserves system resources (CPU/memory)
     /// 2. **Priority Adjustment**: Modifies task priority based on system state
     /// 3. **Load Balancing**: Distributes task to optimal worker
-    /// 4. **Scheduling**: Queues task for execution
+    /// 4. **Fair Queuing**: Queues task behind its dot's earlier tasks
+    /// 5. **Scheduling**: Hands queued tasks to the scheduler in fair-share order
     ///
     /// # Arguments
     /// - `task`: Task to execute
@@ -59,18 +87,69 @@ impl ExecutionController {
     /// # Returns
     /// - `Ok(())`: On successful pipeline execution
     /// - `Err(ExecutionError)`: First error encountered in pipeline stages
+    ///
+    /// Resources stay allocated once the task is queued; call [`Self::release_task`]
+    /// when it finishes or is cancelled. If a later stage fails, or the returned future
+    /// is dropped before queuing completes, the allocation is released here. The task may
+    /// wait in its dot's queue until the scheduler has a free slot.
     pub async fn execute_task(&mut self, task: Task) -> Result<(), ExecutionError> {
         let resources = self.resource_allocator.allocate_resources(&task).await?;
+        let guard = AllocationGuard {
+            allocator: &self.resource_allocator,
+            task_id: task.id,
+            armed: true,
+        };
 
         let mut adjusted_task = self.priority_executor.adjust_priority(task);
         adjusted_task.resource_requirements = resources;
 
         self.load_balancer.distribute_task(&adjusted_task).await?;
 
-        self.scheduler.submit_task(adjusted_task).await?;
+        self.fair_queue.enqueue(adjusted_task)?;
 
+        guard.disarm();
+        self.dispatch_ready().await
+    }
+
+    /// Hands queued tasks to the scheduler, in fair-share order, while it has free slots
+    pub async fn dispatch_ready(&self) -> Result<(), ExecutionError> {
+        while let Some(task) = self.fair_queue.try_dispatch() {
+            let task_id = task.id;
+            if let Err(e) = self.scheduler.submit_task(task).await {
+                self.release_task(task_id);
+                return Err(e);
+            }
+        }
         Ok(())
     }
+
+    /// Credits a finished or cancelled task's resources back to the system and its dot's quota
+    /// and frees its scheduler slot; call [`Self::dispatch_ready`] to fill the slot
+    pub fn release_task(&self, task_id: u64) {
+        self.resource_allocator.release(task_id);
+        self.fair_queue.complete(task_id);
+    }
+}
+
+/// Releases a task's allocation on drop unless the task made it into the scheduler
+struct AllocationGuard<'a> {
+    allocator: &'a ResourceAllocator,
+    task_id: u64,
+    armed: bool,
+}
+
+impl AllocationGuard<'_> {
+    fn disarm(mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for AllocationGuard<'_> {
+    fn drop(&mut self) {
+        if self.armed {
+            self.allocator.release(self.task_id);
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -78,6 +157,8 @@ pub struct Task {
     pub id: u64,
     pub priority: TaskPriority,
     pub resource_requirements: ResourceRequirements,
+    /// Dot that owns the task; its quota is charged for the allocation
+    pub dot_id: Option<String>,
 }
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
@@ -98,6 +179,7 @@ pub struct ResourceRequirements {
 #[derive(Debug)]
 pub enum ExecutionError {
     ResourceAllocationFailure,
+    QuotaExceeded { dot_id: String, resource: QuotaResource },
     TaskDistributionFailure,
     SchedulerOverload,
 }
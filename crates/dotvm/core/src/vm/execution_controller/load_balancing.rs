@@ -117,6 +117,7 @@ mod tests {
             id: 1,
             priority: Default::default(),
             resource_requirements: Default::default(),
+            dot_id: None,
         };
 
         let result = load_balancer.distribute_task(&task).await;
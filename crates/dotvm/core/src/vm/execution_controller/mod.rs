@@ -15,11 +15,15 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 // Sub-modules
+pub mod fair_queue;
 pub mod lib;
 pub mod load_balancing;
 pub mod priority_execution;
+pub mod quota;
 pub mod resource_allocation;
 pub mod work_stealing_scheduler;
 
 // Public exports
+pub use fair_queue::{DotQueueStatus, FairQueue, FairShareConfig};
 pub use lib::{ExecutionController, ExecutionError, ResourceRequirements, Task, TaskPriority};
+pub use quota::{DotQuotaStatus, QuotaConfig, QuotaResource, QuotaUsage};
@@ -106,6 +106,7 @@ mod tests {
             id: 1,
             priority: TaskPriority::Low,
             resource_requirements: Default::default(),
+            dot_id: None,
         };
 
         let adjusted_task = executor.adjust_priority(task);
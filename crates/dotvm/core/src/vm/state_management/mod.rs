@@ -25,7 +25,7 @@ pub mod tree;
 pub mod verification;
 
 // Public re-exports
-pub use lib::{Error, Result, StateKey, StateValue};
+pub use lib::{Error, Result, ShardedMap, StateKey, StateValue};
 pub use mvcc::{MVCCStore, Version, VersionedValue, WriteOperation};
 pub use snapshot::{Snapshot, SnapshotManager, SnapshotMetadata};
 pub use tree::{MerkleNode, MerkleProof, MerkleTree, StateHash};
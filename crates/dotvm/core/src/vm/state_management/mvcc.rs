@@ -19,9 +19,10 @@
 //! This module provides the foundation for handling concurrent state
 //! operations by maintaining multiple versions of state entries.
 
-use crate::vm::state_management::lib::{Result, SharedMap, StateKey, StateValue, new_shared_map}; // Removed Error, current_timestamp
+use crate::vm::state_management::lib::{Result, SharedMap, StateKey, StateValue, new_shared_map, new_shared_map_with_shards}; // Removed Error, current_timestamp
+use parking_lot::Mutex;
 use std::collections::BTreeMap;
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 /// Version identifier used for MVCC
 pub type Version = u64;
@@ -68,12 +69,19 @@ pub enum WriteOperation {
 }
 
 /// MVCC store implementation for maintaining versioned state data
+///
+/// Reads never wait for a transaction to finish: a transaction writes its
+/// versions into the sharded map first and only then publishes its version
+/// number, and reads never look past the published version, so a transaction
+/// becomes visible all at once.
 #[derive(Debug)]
 pub struct MVCCStore {
     /// The main storage for all versions of all keys
     versions: SharedMap<StateKey, Vec<VersionedValue>>,
-    /// Lock for coordinating version allocation
-    version_lock: Arc<RwLock<Version>>,
+    /// Latest committed version, published after all of its writes are in place
+    current_version: AtomicU64,
+    /// Serializes transactions
+    commit_lock: Mutex<()>,
 }
 
 /// MVCC implementation for concurrent state management.
@@ -81,22 +89,34 @@ pub struct MVCCStore {
 impl MVCCStore {
     /// Creates a new MVCC store
     pub fn new() -> Self {
+        Self::with_versions(new_shared_map())
+    }
+
+    /// Creates a new MVCC store whose version map has the given number of shards
+    pub fn with_shards(shards: usize) -> Self {
+        Self::with_versions(new_shared_map_with_shards(shards))
+    }
+
+    fn with_versions(versions: SharedMap<StateKey, Vec<VersionedValue>>) -> Self {
         Self {
-            versions: new_shared_map(),
-            version_lock: Arc::new(RwLock::new(0)),
+            versions,
+            current_version: AtomicU64::new(0),
+            commit_lock: Mutex::new(()),
         }
     }
 
     /// Gets the current latest version
     pub fn current_version(&self) -> Version {
-        *self.version_lock.read().unwrap()
+        self.current_version.load(Ordering::Acquire)
     }
 
     /// Reads a value at the specified version
+    ///
+    /// Versions past the current one read the current version.
     pub fn read(&self, key: &StateKey, version: Version) -> Result<Option<StateValue>> {
-        let versions_map = self.versions.read().unwrap();
+        let version = version.min(self.current_version());
 
-        if let Some(versions) = versions_map.get(key) {
+        if let Some(versions) = self.versions.get(key) {
             // Find the latest version that's visible at the requested version
             for v in versions.iter().rev() {
                 if v.is_visible_at(version) {
@@ -126,21 +146,18 @@ impl MVCCStore {
     /// - `Version`: New version number after commit
     /// - `Error`: On version conflicts or lock poisoning
     pub fn transaction(&self, operations: Vec<WriteOperation>) -> Result<Version> {
-        // Acquire write lock to ensure atomic transaction
-        let mut version_guard = self.version_lock.write().unwrap();
-        let next_version = *version_guard + 1;
-
-        // Apply all operations
-        let mut versions_map = self.versions.write().unwrap();
+        // Hold the commit lock to serialize transactions
+        let _commit = self.commit_lock.lock();
+        let next_version = self.current_version() + 1;
 
+        // Apply all operations; readers ignore them until the version is published
         for op in operations {
             match op {
                 WriteOperation::Put(key, value) => {
-                    let entry = versions_map.entry(key).or_default();
-                    entry.push(VersionedValue::new(value, next_version));
+                    self.versions.get_or_default(key).push(VersionedValue::new(value, next_version));
                 }
                 WriteOperation::Delete(key) => {
-                    if let Some(versions) = versions_map.get_mut(&key) {
+                    if let Some(mut versions) = self.versions.get_mut(&key) {
                         // Mark the latest visible version as deleted
                         for v in versions.iter_mut().rev() {
                             if v.deleted_at.is_none() {
@@ -153,8 +170,8 @@ impl MVCCStore {
             }
         }
 
-        // Update the current version
-        *version_guard = next_version;
+        // Publish the version, making every write above visible at once
+        self.current_version.store(next_version, Ordering::Release);
 
         Ok(next_version)
     }
@@ -170,18 +187,18 @@ impl MVCCStore {
     }
 
     /// Gets a map of all keys and their values at a specific version
+    ///
+    /// Transactions committing during the scan do not affect the result, so
+    /// snapshots can be taken without blocking writers.
     pub fn get_state_at_version(&self, version: Version) -> BTreeMap<StateKey, StateValue> {
+        let version = version.min(self.current_version());
         let mut result = BTreeMap::new();
-        let versions_map = self.versions.read().unwrap();
 
-        for (key, versions) in versions_map.iter() {
-            for v in versions.iter().rev() {
-                if v.is_visible_at(version) {
-                    result.insert(key.clone(), v.value.clone());
-                    break;
-                }
+        self.versions.for_each(|key, versions| {
+            if let Some(v) = versions.iter().rev().find(|v| v.is_visible_at(version)) {
+                result.insert(key.clone(), v.value.clone());
             }
-        }
+        });
 
         result
     }
@@ -308,4 +325,36 @@ mod tests {
         let state3 = store.get_state_at_version(version3);
         assert_eq!(state3.len(), 2); // Only key2 and key3
     }
+
+    #[test]
+    fn test_concurrent_readers_see_whole_transactions() {
+        let store = MVCCStore::with_shards(4);
+        let keys: Vec<StateKey> = (0..16).map(|i| StateKey::from_string(&format!("key{i}"))).collect();
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for round in 0..200 {
+                    let value = StateValue::from_string(&round.to_string());
+                    store.transaction(keys.iter().map(|key| WriteOperation::Put(key.clone(), value.clone())).collect()).unwrap();
+                }
+            });
+
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..200 {
+                        // Every key was written by the same transactions, so a
+                        // version shows them all with one value or none of them
+                        let state = store.get_state_at_version(Version::MAX);
+                        assert!(state.is_empty() || state.len() == keys.len());
+                        let mut values = state.values();
+                        if let Some(first) = values.next() {
+                            assert!(values.all(|value| value == first));
+                        }
+                    }
+                });
+            }
+        });
+
+        assert_eq!(store.current_version(), 200);
+    }
 }
@@ -0,0 +1,432 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Fixed-width integers for the extended architectures
+//!
+//! A `WideInt` holds an architecture word of 64 to 512 bits as a `BigUint` in
+//! `[0, 2^bits)`. Arithmetic wraps at the width like the native 64-bit opcodes do;
+//! the signed operations read the same bits as two's complement.
+
+use num_bigint::{BigInt, BigUint, Sign};
+use num_traits::{One, Zero};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Narrowest width a wide value can have; narrower values live on the stack as `Int64`
+pub const MIN_WIDE_BITS: u32 = 64;
+/// Widest architecture word
+pub const MAX_WIDE_BITS: u32 = 512;
+
+/// Errors from wide arithmetic
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum WideArithmeticError {
+    #[error("Width mismatch: {left}-bit and {right}-bit operands; convert explicitly first")]
+    WidthMismatch { left: u32, right: u32 },
+
+    #[error("Division by zero")]
+    DivisionByZero,
+
+    #[error("Invalid shift amount: {0}")]
+    InvalidShift(i64),
+
+    #[error("Cannot {operation} a {from}-bit value to {to} bits")]
+    InvalidConversion { operation: &'static str, from: u32, to: u32 },
+
+    #[error("Unsupported width: {0} bits")]
+    UnsupportedWidth(u32),
+}
+
+pub type WideArithmeticResult<T> = Result<T, WideArithmeticError>;
+
+/// Integer of a fixed bit width
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "WideIntRepr", try_from = "WideIntRepr")]
+pub struct WideInt {
+    bits: u32,
+    value: BigUint,
+}
+
+/// Serialized form: the width and the value's little-endian bytes
+#[derive(Serialize, Deserialize)]
+struct WideIntRepr {
+    bits: u32,
+    le_bytes: Vec<u8>,
+}
+
+impl From<WideInt> for WideIntRepr {
+    fn from(value: WideInt) -> Self {
+        Self {
+            bits: value.bits,
+            le_bytes: value.value.to_bytes_le(),
+        }
+    }
+}
+
+impl TryFrom<WideIntRepr> for WideInt {
+    type Error = WideArithmeticError;
+
+    fn try_from(repr: WideIntRepr) -> Result<Self, Self::Error> {
+        Self::new(repr.bits, BigUint::from_bytes_le(&repr.le_bytes))
+    }
+}
+
+fn check_width(bits: u32) -> WideArithmeticResult<()> {
+    if bits.is_power_of_two() && (MIN_WIDE_BITS..=MAX_WIDE_BITS).contains(&bits) {
+        Ok(())
+    } else {
+        Err(WideArithmeticError::UnsupportedWidth(bits))
+    }
+}
+
+impl WideInt {
+    /// `value` reduced modulo `2^bits`
+    pub fn new(bits: u32, value: BigUint) -> WideArithmeticResult<Self> {
+        check_width(bits)?;
+        Ok(Self::wrapping(bits, value))
+    }
+
+    /// Two's complement encoding of `value` at `bits`, wrapping if it does not fit
+    pub fn from_signed(bits: u32, value: &BigInt) -> WideArithmeticResult<Self> {
+        check_width(bits)?;
+        Ok(Self::wrapping_signed(bits, value))
+    }
+
+    /// `value` sign-extended to `bits`
+    pub fn from_i64(bits: u32, value: i64) -> WideArithmeticResult<Self> {
+        Self::from_signed(bits, &BigInt::from(value))
+    }
+
+    fn wrapping(bits: u32, value: BigUint) -> Self {
+        let value = if value.bits() > u64::from(bits) { value & Self::mask(bits) } else { value };
+        Self { bits, value }
+    }
+
+    fn wrapping_signed(bits: u32, value: &BigInt) -> Self {
+        let value = match value.sign() {
+            Sign::Minus => {
+                // -x is 2^bits - (x mod 2^bits)
+                let magnitude = value.magnitude() & Self::mask(bits);
+                if magnitude.is_zero() { magnitude } else { (BigUint::one() << bits) - magnitude }
+            }
+            _ => value.magnitude() & Self::mask(bits),
+        };
+        Self { bits, value }
+    }
+
+    fn mask(bits: u32) -> BigUint {
+        (BigUint::one() << bits) - BigUint::one()
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// The bits read as an unsigned integer
+    pub fn value(&self) -> &BigUint {
+        &self.value
+    }
+
+    /// The bits read as a two's complement integer
+    pub fn to_signed(&self) -> BigInt {
+        if self.is_negative() {
+            BigInt::from(self.value.clone()) - (BigInt::one() << self.bits)
+        } else {
+            BigInt::from(self.value.clone())
+        }
+    }
+
+    /// Whether the top bit is set
+    pub fn is_negative(&self) -> bool {
+        self.value.bit(u64::from(self.bits - 1))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.value.is_zero()
+    }
+
+    /// Little-endian bytes, `bits / 8` of them
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.value.to_bytes_le();
+        bytes.resize(self.bits as usize / 8, 0);
+        bytes
+    }
+
+    /// The low 64 bits as a two's complement `i64`
+    pub fn low_i64(&self) -> i64 {
+        self.value.iter_u64_digits().next().unwrap_or(0) as i64
+    }
+
+    fn same_width(&self, other: &Self) -> WideArithmeticResult<()> {
+        if self.bits == other.bits {
+            Ok(())
+        } else {
+            Err(WideArithmeticError::WidthMismatch { left: self.bits, right: other.bits })
+        }
+    }
+
+    pub fn wrapping_add(&self, other: &Self) -> WideArithmeticResult<Self> {
+        self.same_width(other)?;
+        Ok(Self::wrapping(self.bits, &self.value + &other.value))
+    }
+
+    pub fn wrapping_sub(&self, other: &Self) -> WideArithmeticResult<Self> {
+        self.same_width(other)?;
+        // Adding 2^bits keeps the difference non-negative before wrapping
+        Ok(Self::wrapping(self.bits, (&self.value + (BigUint::one() << self.bits)) - &other.value))
+    }
+
+    pub fn wrapping_mul(&self, other: &Self) -> WideArithmeticResult<Self> {
+        self.same_width(other)?;
+        Ok(Self::wrapping(self.bits, &self.value * &other.value))
+    }
+
+    pub fn div_unsigned(&self, other: &Self) -> WideArithmeticResult<Self> {
+        self.same_width(other)?;
+        if other.is_zero() {
+            return Err(WideArithmeticError::DivisionByZero);
+        }
+        Ok(Self::wrapping(self.bits, &self.value / &other.value))
+    }
+
+    pub fn rem_unsigned(&self, other: &Self) -> WideArithmeticResult<Self> {
+        self.same_width(other)?;
+        if other.is_zero() {
+            return Err(WideArithmeticError::DivisionByZero);
+        }
+        Ok(Self::wrapping(self.bits, &self.value % &other.value))
+    }
+
+    /// Signed division rounding toward zero; the minimum value divided by -1 wraps to itself
+    pub fn div_signed(&self, other: &Self) -> WideArithmeticResult<Self> {
+        self.same_width(other)?;
+        if other.is_zero() {
+            return Err(WideArithmeticError::DivisionByZero);
+        }
+        Ok(Self::wrapping_signed(self.bits, &(self.to_signed() / other.to_signed())))
+    }
+
+    /// Signed remainder, taking the sign of the dividend
+    pub fn rem_signed(&self, other: &Self) -> WideArithmeticResult<Self> {
+        self.same_width(other)?;
+        if other.is_zero() {
+            return Err(WideArithmeticError::DivisionByZero);
+        }
+        Ok(Self::wrapping_signed(self.bits, &(self.to_signed() % other.to_signed())))
+    }
+
+    pub fn cmp_unsigned(&self, other: &Self) -> WideArithmeticResult<Ordering> {
+        self.same_width(other)?;
+        Ok(self.value.cmp(&other.value))
+    }
+
+    pub fn cmp_signed(&self, other: &Self) -> WideArithmeticResult<Ordering> {
+        self.same_width(other)?;
+        Ok(self.to_signed().cmp(&other.to_signed()))
+    }
+
+    /// Shift left, dropping bits shifted past the width
+    pub fn shl(&self, amount: u64) -> Self {
+        if amount >= u64::from(self.bits) {
+            return Self::wrapping(self.bits, BigUint::zero());
+        }
+        Self::wrapping(self.bits, &self.value << amount)
+    }
+
+    /// Logical shift right, filling with zeros
+    pub fn shr_unsigned(&self, amount: u64) -> Self {
+        if amount >= u64::from(self.bits) {
+            return Self::wrapping(self.bits, BigUint::zero());
+        }
+        Self::wrapping(self.bits, &self.value >> amount)
+    }
+
+    /// Arithmetic shift right, filling with the sign bit
+    pub fn shr_signed(&self, amount: u64) -> Self {
+        let amount = amount.min(u64::from(self.bits));
+        // BigInt shifts negative values toward negative infinity, as an arithmetic shift does
+        Self::wrapping_signed(self.bits, &(self.to_signed() >> amount))
+    }
+
+    /// The same value at a width at least as large, with the new upper bits zero
+    pub fn zero_extend(&self, bits: u32) -> WideArithmeticResult<Self> {
+        check_width(bits)?;
+        if bits < self.bits {
+            return Err(WideArithmeticError::InvalidConversion {
+                operation: "zero-extend",
+                from: self.bits,
+                to: bits,
+            });
+        }
+        Ok(Self::wrapping(bits, self.value.clone()))
+    }
+
+    /// The same signed value at a width at least as large, with the new upper bits copied from the sign bit
+    pub fn sign_extend(&self, bits: u32) -> WideArithmeticResult<Self> {
+        check_width(bits)?;
+        if bits < self.bits {
+            return Err(WideArithmeticError::InvalidConversion {
+                operation: "sign-extend",
+                from: self.bits,
+                to: bits,
+            });
+        }
+        Ok(Self::wrapping_signed(bits, &self.to_signed()))
+    }
+
+    /// The low `bits` bits, for a width at most as large
+    pub fn truncate(&self, bits: u32) -> WideArithmeticResult<Self> {
+        check_width(bits)?;
+        if bits > self.bits {
+            return Err(WideArithmeticError::InvalidConversion {
+                operation: "truncate",
+                from: self.bits,
+                to: bits,
+            });
+        }
+        Ok(Self::wrapping(bits, self.value.clone()))
+    }
+}
+
+impl fmt::Display for WideInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}u{}", self.value, self.bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bits: u32, digits: &str) -> WideInt {
+        WideInt::new(bits, BigUint::parse_bytes(digits.as_bytes(), 16).unwrap()).unwrap()
+    }
+
+    fn max(bits: u32) -> WideInt {
+        WideInt::new(bits, WideInt::mask(bits)).unwrap()
+    }
+
+    #[test]
+    fn test_rejects_unsupported_widths() {
+        assert_eq!(WideInt::new(32, BigUint::one()), Err(WideArithmeticError::UnsupportedWidth(32)));
+        assert_eq!(WideInt::new(192, BigUint::one()), Err(WideArithmeticError::UnsupportedWidth(192)));
+        assert_eq!(WideInt::new(1024, BigUint::one()), Err(WideArithmeticError::UnsupportedWidth(1024)));
+    }
+
+    #[test]
+    fn test_128_bit_vectors() {
+        // (2^64 - 1)^2 = 2^128 - 2^65 + 1
+        let a = hex(128, "ffffffffffffffff");
+        assert_eq!(a.wrapping_mul(&a).unwrap(), hex(128, "fffffffffffffffe0000000000000001"));
+
+        // 0x0123456789abcdef0123456789abcdef * 0xfedcba9876543210 mod 2^128
+        let b = hex(128, "0123456789abcdef0123456789abcdef");
+        let c = hex(128, "fedcba9876543210");
+        assert_eq!(b.wrapping_mul(&c).unwrap(), hex(128, "2358d29092d964322236d88fe5618cf0"));
+
+        assert_eq!(max(128).wrapping_add(&hex(128, "1")).unwrap(), hex(128, "0"));
+        assert_eq!(hex(128, "0").wrapping_sub(&hex(128, "1")).unwrap(), max(128));
+        assert_eq!(hex(128, "fffffffffffffffe0000000000000001").div_unsigned(&a).unwrap(), a);
+        assert_eq!(max(128).rem_unsigned(&hex(128, "10000000000000000")).unwrap(), a);
+    }
+
+    #[test]
+    fn test_256_bit_vectors() {
+        // (2^128 + 1)^2 = 2^256 + 2^129 + 1, which wraps to 2^129 + 1
+        let a = hex(256, "100000000000000000000000000000001");
+        assert_eq!(a.wrapping_mul(&a).unwrap(), hex(256, "200000000000000000000000000000001"));
+
+        // secp256k1 field prime p = 2^256 - 0x1000003d1
+        let p = hex(256, "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f");
+        assert_eq!(max(256).rem_unsigned(&p).unwrap(), hex(256, "1000003d0"));
+        assert_eq!(max(256).wrapping_mul(&max(256)).unwrap(), hex(256, "1"));
+        assert_eq!(p.wrapping_add(&hex(256, "1000003d1")).unwrap(), hex(256, "0"));
+    }
+
+    #[test]
+    fn test_512_bit_vectors() {
+        // 2^256 * 2^256 overflows to zero; (2^256 - 1) * (2^256 + 1) = 2^512 - 1
+        let low = max(512).truncate(256).unwrap().zero_extend(512).unwrap();
+        let high = hex(512, "1").shl(256);
+        assert_eq!(high.wrapping_mul(&high).unwrap(), hex(512, "0"));
+        assert_eq!(low.wrapping_mul(&high.wrapping_add(&hex(512, "1")).unwrap()).unwrap(), max(512));
+        assert_eq!(max(512).div_unsigned(&low).unwrap(), high.wrapping_add(&hex(512, "1")).unwrap());
+    }
+
+    #[test]
+    fn test_signed_operations() {
+        let minus_seven = WideInt::from_i64(128, -7).unwrap();
+        let two = WideInt::from_i64(128, 2).unwrap();
+        assert_eq!(*minus_seven.value(), WideInt::mask(128) - BigUint::from(6u8));
+        assert_eq!(minus_seven.to_signed(), BigInt::from(-7));
+
+        assert_eq!(minus_seven.div_signed(&two).unwrap(), WideInt::from_i64(128, -3).unwrap());
+        assert_eq!(minus_seven.rem_signed(&two).unwrap(), WideInt::from_i64(128, -1).unwrap());
+        assert_eq!(minus_seven.cmp_signed(&two).unwrap(), Ordering::Less);
+        assert_eq!(minus_seven.cmp_unsigned(&two).unwrap(), Ordering::Greater);
+
+        // MIN / -1 wraps to MIN
+        let min = hex(256, "1").shl(255);
+        let minus_one = WideInt::from_i64(256, -1).unwrap();
+        assert_eq!(min.div_signed(&minus_one).unwrap(), min);
+        assert_eq!(min.rem_signed(&minus_one).unwrap(), hex(256, "0"));
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let zero = hex(128, "0");
+        assert_eq!(hex(128, "1").div_unsigned(&zero), Err(WideArithmeticError::DivisionByZero));
+        assert_eq!(hex(128, "1").rem_signed(&zero), Err(WideArithmeticError::DivisionByZero));
+    }
+
+    #[test]
+    fn test_shifts() {
+        let one = hex(128, "1");
+        assert_eq!(one.shl(127), hex(128, "80000000000000000000000000000000"));
+        assert_eq!(one.shl(128), hex(128, "0"));
+        assert_eq!(max(128).shr_unsigned(120), hex(128, "ff"));
+        assert_eq!(max(128).shr_unsigned(u64::MAX), hex(128, "0"));
+
+        let negative = hex(128, "80000000000000000000000000000000");
+        assert_eq!(negative.shr_signed(124), WideInt::from_i64(128, -8).unwrap());
+        assert_eq!(negative.shr_signed(500), max(128));
+        assert_eq!(hex(128, "7f").shr_signed(4), hex(128, "7"));
+    }
+
+    #[test]
+    fn test_width_conversions() {
+        let minus_two = WideInt::from_i64(128, -2).unwrap();
+        assert_eq!(minus_two.zero_extend(256).unwrap(), hex(256, "fffffffffffffffffffffffffffffffe"));
+        assert_eq!(minus_two.sign_extend(256).unwrap(), WideInt::from_i64(256, -2).unwrap());
+        assert_eq!(minus_two.truncate(64).unwrap().low_i64(), -2);
+        assert_eq!(
+            hex(256, "123456789abcdef00112233445566778899aabbccddeeff").truncate(128).unwrap(),
+            hex(128, "112233445566778899aabbccddeeff")
+        );
+
+        assert!(matches!(minus_two.truncate(256), Err(WideArithmeticError::InvalidConversion { .. })));
+        assert!(matches!(minus_two.sign_extend(64), Err(WideArithmeticError::InvalidConversion { .. })));
+        assert_eq!(hex(128, "1").wrapping_add(&hex(256, "1")), Err(WideArithmeticError::WidthMismatch { left: 128, right: 256 }));
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let value = WideInt::from_i64(512, -12345).unwrap();
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(serde_json::from_str::<WideInt>(&json).unwrap(), value);
+        assert!(serde_json::from_str::<WideInt>(r#"{"bits":100,"le_bytes":[1]}"#).is_err());
+    }
+}
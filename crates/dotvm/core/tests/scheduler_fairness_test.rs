@@ -0,0 +1,163 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Load test for per-dot fair queuing
+//!
+//! Simulates a pool of workers in discrete ticks: one dot floods the queue with thousands of
+//! tasks while a quiet dot submits a task at a steady pace. [`FairQueue`] documents that at most
+//! `⌊w_flood / w_quiet⌋ + 1` flood tasks are dispatched ahead of a waiting quiet task, so with
+//! `W` workers and a fixed service time `S` the flood may add at most `S * ⌈(bound + 1) / W⌉`
+//! ticks to the quiet dot's p99 latency.
+
+use dotvm_core::vm::execution_controller::{FairQueue, FairShareConfig, Task, TaskPriority};
+use std::collections::HashMap;
+
+const WORKERS: usize = 4;
+const SERVICE_TICKS: u64 = 10;
+const QUIET_INTERVAL: u64 = 25;
+const QUIET_TASKS: usize = 200;
+const FLOOD_TASKS: u64 = 5_000;
+const QUIET_ID_BASE: u64 = 1_000_000;
+
+struct Scenario {
+    flood: bool,
+    /// Owners of the flood and quiet tasks; the same owner shares a single FIFO queue
+    flood_dot: Option<&'static str>,
+    quiet_dot: Option<&'static str>,
+    flood_weight: u32,
+}
+
+struct Outcome {
+    quiet_latencies: Vec<u64>,
+    queue: FairQueue,
+}
+
+fn task(id: u64, dot_id: Option<&str>) -> Task {
+    Task {
+        id,
+        priority: TaskPriority::Medium,
+        resource_requirements: Default::default(),
+        dot_id: dot_id.map(str::to_string),
+    }
+}
+
+fn run(scenario: Scenario) -> Outcome {
+    let queue = FairQueue::new(FairShareConfig {
+        default_weight: 1,
+        max_in_flight: WORKERS,
+        max_queue_depth: usize::MAX,
+    });
+    if let Some(flood_dot) = scenario.flood_dot {
+        queue.set_weight(flood_dot, scenario.flood_weight);
+    }
+
+    let mut running: Vec<(u64, u64)> = Vec::new();
+    let mut arrivals = HashMap::new();
+    let mut quiet_latencies = Vec::new();
+    let mut tick = 0;
+    while quiet_latencies.len() < QUIET_TASKS {
+        running.retain(|&(finish, task_id)| finish > tick || !queue.complete(task_id));
+
+        if scenario.flood && tick == 0 {
+            for id in 0..FLOOD_TASKS {
+                queue.enqueue(task(id, scenario.flood_dot)).unwrap();
+            }
+        }
+        if tick % QUIET_INTERVAL == 0 && arrivals.len() < QUIET_TASKS {
+            let id = QUIET_ID_BASE + arrivals.len() as u64;
+            queue.enqueue(task(id, scenario.quiet_dot)).unwrap();
+            arrivals.insert(id, tick);
+        }
+
+        while let Some(task) = queue.try_dispatch() {
+            running.push((tick + SERVICE_TICKS, task.id));
+            if let Some(arrival) = arrivals.get(&task.id) {
+                quiet_latencies.push(tick + SERVICE_TICKS - arrival);
+            }
+        }
+        tick += 1;
+    }
+
+    Outcome { quiet_latencies, queue }
+}
+
+fn p99(latencies: &[u64]) -> u64 {
+    let mut sorted = latencies.to_vec();
+    sorted.sort_unstable();
+    sorted[(sorted.len() * 99).div_ceil(100) - 1]
+}
+
+/// Extra latency the flood may cause according to the queue's fairness bound
+fn allowed_increase(queue: &FairQueue) -> u64 {
+    let bound = queue.fairness_bound(Some("quiet"));
+    SERVICE_TICKS * (bound + 1).div_ceil(WORKERS) as u64
+}
+
+fn fair(flood: bool, flood_weight: u32) -> Scenario {
+    Scenario {
+        flood,
+        flood_dot: Some("flood"),
+        quiet_dot: Some("quiet"),
+        flood_weight,
+    }
+}
+
+#[test]
+fn flooding_dot_does_not_raise_quiet_dot_p99_beyond_bound() {
+    let baseline = p99(&run(fair(false, 1)).quiet_latencies);
+    assert_eq!(baseline, SERVICE_TICKS);
+
+    let flooded = run(fair(true, 1));
+    let increase = p99(&flooded.quiet_latencies) - baseline;
+    assert_eq!(flooded.queue.fairness_bound(Some("quiet")), 2);
+    assert!(
+        increase <= allowed_increase(&flooded.queue),
+        "p99 grew by {increase} ticks, bound is {}",
+        allowed_increase(&flooded.queue)
+    );
+
+    let quiet = flooded.queue.status(Some("quiet")).unwrap();
+    assert_eq!(quiet.starved, 0);
+    assert_eq!(quiet.dispatched, QUIET_TASKS as u64);
+    assert!(flooded.queue.status(Some("flood")).unwrap().queued > 0, "the flood must still be backlogged at the end");
+}
+
+#[test]
+fn heavier_flooding_dot_stays_within_weighted_bound() {
+    let baseline = p99(&run(fair(false, 4)).quiet_latencies);
+    let flooded = run(fair(true, 4));
+    let increase = p99(&flooded.quiet_latencies) - baseline;
+
+    assert_eq!(flooded.queue.fairness_bound(Some("quiet")), 5);
+    assert!(
+        increase <= allowed_increase(&flooded.queue),
+        "p99 grew by {increase} ticks, bound is {}",
+        allowed_increase(&flooded.queue)
+    );
+    assert_eq!(flooded.queue.status(Some("quiet")).unwrap().starved, 0);
+}
+
+#[test]
+fn shared_queue_lets_flood_starve_quiet_tasks() {
+    // Without per-dot queues the quiet tasks wait behind the whole flood
+    let shared = run(Scenario {
+        flood: true,
+        flood_dot: None,
+        quiet_dot: None,
+        flood_weight: 1,
+    });
+    assert!(p99(&shared.quiet_latencies) > 100 * SERVICE_TICKS);
+}
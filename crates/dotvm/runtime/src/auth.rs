@@ -0,0 +1,588 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Authentication and authorization for the runtime's gRPC server
+//!
+//! Callers present an API key as `authorization: Bearer <key>` or `x-api-key: <key>`.
+//! A [`CredentialStore`] resolves the key to the id and [`AccessLevel`] it was
+//! issued with, and the [`MethodPolicy`] names the level each method needs:
+//! readers may query status and list, operators may also deploy, execute and
+//! write, and only admins may delete dots or change configuration.
+//!
+//! The check runs as a tower layer around the whole server rather than as a
+//! tonic interceptor, because interceptors are not told which method is called.
+//! Refused calls get UNAUTHENTICATED or PERMISSION_DENIED without the reason,
+//! which is logged instead, and refusals are counted per key id for alerting in the
+//! `dotvm_auth_rejections_total` metric.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use futures::future::BoxFuture;
+use metrics::counter;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tonic::Status;
+use tonic::body::BoxBody;
+use tonic::codegen::http::{HeaderMap, Request, Response, header::AUTHORIZATION};
+use tower::{Layer, Service};
+use tracing::warn;
+
+/// Header carrying an API key as an alternative to a bearer token
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Key id that refusals of calls without any key are counted under
+pub const ANONYMOUS_KEY_ID: &str = "anonymous";
+
+/// Key id that refusals of keys with an unrecognized id are counted under
+pub const UNKNOWN_KEY_ID: &str = "unknown";
+
+/// Path prefix of the gRPC reflection service
+const REFLECTION_PREFIX: &str = "/grpc.reflection.";
+
+/// Health check methods, public unless configured otherwise
+const HEALTH_METHODS: [&str; 3] = ["/vm_service.VmService/HealthCheck", "/database_service.DatabaseService/HealthCheck", "/grpc.health.v1.Health/Check"];
+
+/// Methods that only read, open to reader keys
+const READER_METHODS: [&str; 39] = [
+    "/runtime.Runtime/Ping",
+    "/vm_service.VmService/Ping",
+    "/vm_service.VmService/GetVMStatus",
+    "/vm_service.VmService/GetVMMetrics",
+    "/vm_service.VmService/GetArchitectures",
+    "/vm_service.VmService/ListDots",
+    "/vm_service.VmService/GetDotState",
+    "/vm_service.VmService/GetStateDiff",
+    "/vm_service.VmService/GetDotQuota",
+    "/vm_service.VmService/GetDotCacheStats",
+    "/vm_service.VmService/ListCheckpoints",
+    "/vm_service.VmService/GetBytecode",
+    "/vm_service.VmService/ValidateBytecode",
+    "/vm_service.VmService/GetDotABI",
+    "/vm_service.VmService/ValidateABI",
+    "/vm_service.VmService/ListABIVersions",
+    "/vm_service.VmService/StreamDotEvents",
+    "/vm_service.VmService/StreamVMMetrics",
+    "/vm_service.VmService/QueryLogs",
+    "/cluster_service.ClusterService/ListNodes",
+    "/cluster_service.ClusterService/GetNode",
+    "/cluster_service.ClusterService/GetNodeHealth",
+    "/cluster_service.ClusterService/ListDeployments",
+    "/cluster_service.ClusterService/GetDeployment",
+    "/cluster_service.ClusterService/GetLoadBalancerStatus",
+    "/cluster_service.ClusterService/GetNodeLoad",
+    "/cluster_service.ClusterService/GetClusterStatus",
+    "/cluster_service.ClusterService/GetClusterMetrics",
+    "/cluster_service.ClusterService/GetClusterConfig",
+    "/cluster_service.ClusterService/StreamNodeEvents",
+    "/cluster_service.ClusterService/StreamDeploymentEvents",
+    "/cluster_service.ClusterService/StreamClusterMetrics",
+    "/database_service.DatabaseService/Get",
+    "/database_service.DatabaseService/ListCollections",
+    "/database_service.DatabaseService/Query",
+    "/database_service.DatabaseService/StreamQuery",
+    "/database_service.DatabaseService/ListIndices",
+    "/database_service.DatabaseService/GetDatabaseStatus",
+    "/database_service.DatabaseService/Ping",
+];
+
+/// Methods that delete or reconfigure, reserved to admin keys
+const ADMIN_METHODS: [&str; 14] = [
+    "/vm_service.VmService/DeleteDot",
+    "/vm_service.VmService/SetDotQuota",
+    "/vm_service.VmService/SetDotCaching",
+    "/vm_service.VmService/RestoreCheckpoint",
+    "/vm_service.VmService/SetMemoryTracking",
+    "/cluster_service.ClusterService/UnregisterNode",
+    "/cluster_service.ClusterService/DeleteDeployment",
+    "/cluster_service.ClusterService/UpdateLoadBalancerConfig",
+    "/cluster_service.ClusterService/DrainNode",
+    "/cluster_service.ClusterService/CordonNode",
+    "/cluster_service.ClusterService/UncordonNode",
+    "/cluster_service.ClusterService/UpdateClusterConfig",
+    "/database_service.DatabaseService/DropCollection",
+    "/database_service.DatabaseService/UpdateStorageConfig",
+];
+
+/// What a key may do, each level including the ones below it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLevel {
+    Reader,
+    Operator,
+    Admin,
+}
+
+/// An authenticated key, added to the extensions of the requests it made
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credential {
+    pub key_id: String,
+    pub level: AccessLevel,
+}
+
+/// A presented key that does not authenticate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rejected {
+    /// Id of the key the caller claimed to hold, when the store knows it
+    pub key_id: Option<String>,
+}
+
+/// Source of truth for API keys, such as a key file or the gateway's auth service
+#[async_trait]
+pub trait CredentialStore: Send + Sync {
+    async fn authenticate(&self, key: &str) -> Result<Credential, Rejected>;
+}
+
+#[derive(Error, Debug)]
+pub enum AuthError {
+    #[error("Failed to read {path}: {source}")]
+    Read { path: PathBuf, source: io::Error },
+    #[error("Invalid key file {path}: {source}")]
+    Parse { path: PathBuf, source: serde_json::Error },
+    #[error("Key '{key_id}' needs a hex encoded SHA-256 digest")]
+    InvalidDigest { key_id: String },
+    #[error("Key id '{key_id}' is listed more than once")]
+    DuplicateKey { key_id: String },
+}
+
+/// Authentication settings of the server
+#[derive(Debug, Clone)]
+pub struct AuthConfig {
+    /// Key file read by [`StaticKeyStore`]
+    pub keys_path: PathBuf,
+    /// Health checks answer without a key; otherwise they need a reader key
+    pub public_health_check: bool,
+    /// Reflection answers without a key; otherwise it needs a reader key
+    pub public_reflection: bool,
+}
+
+#[derive(Deserialize)]
+struct KeyFile {
+    keys: Vec<KeyEntry>,
+}
+
+#[derive(Deserialize)]
+struct KeyEntry {
+    id: String,
+    level: AccessLevel,
+    sha256: String,
+}
+
+/// Keys listed in a JSON file by their SHA-256 digest
+///
+/// Keys take the form `<key id>.<secret>`, so refusals can be told apart by key id:
+///
+/// ```json
+/// { "keys": [{ "id": "deployer", "level": "operator", "sha256": "<hex digest of the whole key>" }] }
+/// ```
+#[derive(Debug, Default)]
+pub struct StaticKeyStore {
+    keys: HashMap<String, (AccessLevel, [u8; 32])>,
+}
+
+impl StaticKeyStore {
+    pub fn load(path: &Path) -> Result<Self, AuthError> {
+        let contents = std::fs::read(path).map_err(|source| AuthError::Read { path: path.to_path_buf(), source })?;
+        let file: KeyFile = serde_json::from_slice(&contents).map_err(|source| AuthError::Parse { path: path.to_path_buf(), source })?;
+
+        let mut store = Self::default();
+        for entry in file.keys {
+            let digest = hex::decode(&entry.sha256)
+                .ok()
+                .and_then(|digest| <[u8; 32]>::try_from(digest).ok())
+                .ok_or_else(|| AuthError::InvalidDigest { key_id: entry.id.clone() })?;
+            store.insert(entry.id, entry.level, digest)?;
+        }
+        Ok(store)
+    }
+
+    /// Add a key by its digest
+    pub fn insert(&mut self, key_id: String, level: AccessLevel, digest: [u8; 32]) -> Result<(), AuthError> {
+        if self.keys.contains_key(&key_id) {
+            return Err(AuthError::DuplicateKey { key_id });
+        }
+        self.keys.insert(key_id, (level, digest));
+        Ok(())
+    }
+
+    /// Digest of a key as the key file lists it
+    pub fn digest(key: &str) -> [u8; 32] {
+        Sha256::digest(key.as_bytes()).into()
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+#[async_trait]
+impl CredentialStore for StaticKeyStore {
+    async fn authenticate(&self, key: &str) -> Result<Credential, Rejected> {
+        // Unknown ids are not echoed into the refusal counts, so guessing cannot grow them
+        let (key_id, (level, digest)) = key.split_once('.').and_then(|(key_id, _)| self.keys.get_key_value(key_id)).ok_or(Rejected { key_id: None })?;
+
+        // Compare without exiting early so timing does not tell how much of the digest matched
+        let presented = Self::digest(key);
+        let difference = presented.iter().zip(digest).fold(0, |difference, (a, b)| difference | (a ^ b));
+        if difference != 0 {
+            return Err(Rejected { key_id: Some(key_id.clone()) });
+        }
+
+        Ok(Credential {
+            key_id: key_id.clone(),
+            level: *level,
+        })
+    }
+}
+
+/// Access level each method requires
+#[derive(Debug, Clone)]
+pub struct MethodPolicy {
+    levels: HashMap<String, AccessLevel>,
+    public_health_check: bool,
+    public_reflection: bool,
+}
+
+impl Default for MethodPolicy {
+    fn default() -> Self {
+        Self::new(true, false)
+    }
+}
+
+impl MethodPolicy {
+    /// The runtime's methods at their default levels; methods not listed need an operator key
+    pub fn new(public_health_check: bool, public_reflection: bool) -> Self {
+        let readers = READER_METHODS.iter().map(|method| (method.to_string(), AccessLevel::Reader));
+        let admins = ADMIN_METHODS.iter().map(|method| (method.to_string(), AccessLevel::Admin));
+        Self {
+            levels: readers.chain(admins).collect(),
+            public_health_check,
+            public_reflection,
+        }
+    }
+
+    /// Require `level` for the method at `path`, such as `/vm_service.VmService/ExecuteDot`
+    pub fn with_level(mut self, path: impl Into<String>, level: AccessLevel) -> Self {
+        self.levels.insert(path.into(), level);
+        self
+    }
+
+    /// Level the method at `path` requires, `None` if it is public
+    pub fn required(&self, path: &str) -> Option<AccessLevel> {
+        if HEALTH_METHODS.contains(&path) {
+            return (!self.public_health_check).then_some(AccessLevel::Reader);
+        }
+        if path.starts_with(REFLECTION_PREFIX) {
+            return (!self.public_reflection).then_some(AccessLevel::Reader);
+        }
+        Some(self.levels.get(path).copied().unwrap_or(AccessLevel::Operator))
+    }
+}
+
+/// Checks the key of every call against the method's required level
+pub struct Authenticator {
+    store: Arc<dyn CredentialStore>,
+    policy: MethodPolicy,
+    rejections: DashMap<String, u64>,
+}
+
+impl Authenticator {
+    pub fn new(store: Arc<dyn CredentialStore>, policy: MethodPolicy) -> Self {
+        Self {
+            store,
+            policy,
+            rejections: DashMap::new(),
+        }
+    }
+
+    /// Authorize a call to the method at `path`, returning the caller's credential unless the method is public
+    pub async fn authorize(&self, path: &str, headers: &HeaderMap) -> Result<Option<Credential>, Status> {
+        let Some(required) = self.policy.required(path) else {
+            return Ok(None);
+        };
+
+        let Some(key) = presented_key(headers) else {
+            return Err(self.reject(ANONYMOUS_KEY_ID, path, "no key presented", unauthenticated()));
+        };
+        let credential = match self.store.authenticate(key).await {
+            Ok(credential) => credential,
+            Err(rejected) => return Err(self.reject(rejected.key_id.as_deref().unwrap_or(UNKNOWN_KEY_ID), path, "invalid key", unauthenticated())),
+        };
+
+        if credential.level < required {
+            return Err(self.reject(&credential.key_id, path, "access level too low", Status::permission_denied("Permission denied")));
+        }
+        Ok(Some(credential))
+    }
+
+    fn reject(&self, key_id: &str, path: &str, reason: &str, status: Status) -> Status {
+        let rejections = {
+            let mut count = self.rejections.entry(key_id.to_string()).or_insert(0);
+            *count += 1;
+            *count
+        };
+        counter!("dotvm_auth_rejections_total", 1, "key_id" => key_id.to_string());
+        warn!(key_id, method = path, reason, rejections, "Refused gRPC call");
+        status
+    }
+
+    /// Refused calls so far by key id, sorted by key id
+    pub fn rejections(&self) -> Vec<(String, u64)> {
+        let mut rejections: Vec<_> = self.rejections.iter().map(|entry| (entry.key().clone(), *entry.value())).collect();
+        rejections.sort();
+        rejections
+    }
+}
+
+/// The same refusal for missing and invalid keys, so callers cannot probe which ids exist
+fn unauthenticated() -> Status {
+    Status::unauthenticated("Authentication required")
+}
+
+/// Key from a bearer token or the API key header
+fn presented_key(headers: &HeaderMap) -> Option<&str> {
+    let bearer = headers.get(AUTHORIZATION).and_then(|value| value.to_str().ok()).and_then(|value| value.strip_prefix("Bearer "));
+    let key = bearer.or_else(|| headers.get(API_KEY_HEADER).and_then(|value| value.to_str().ok()))?.trim();
+    (!key.is_empty()).then_some(key)
+}
+
+/// Layer authorizing every call to the services below it
+#[derive(Clone)]
+pub struct AuthLayer {
+    authenticator: Option<Arc<Authenticator>>,
+}
+
+impl AuthLayer {
+    pub fn new(authenticator: Arc<Authenticator>) -> Self {
+        Self { authenticator: Some(authenticator) }
+    }
+
+    /// Layer letting every call through, for servers without authentication
+    pub fn disabled() -> Self {
+        Self { authenticator: None }
+    }
+}
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = AuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthService {
+            inner,
+            authenticator: self.authenticator.clone(),
+        }
+    }
+}
+
+/// Service answering refused calls itself and passing the others on with their [`Credential`]
+#[derive(Clone)]
+pub struct AuthService<S> {
+    inner: S,
+    authenticator: Option<Arc<Authenticator>>,
+}
+
+impl<S, B> Service<Request<B>> for AuthService<S>
+where
+    S: Service<Request<B>, Response = Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        // The ready service handles this call, a fresh clone waits for the next one
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let Some(authenticator) = self.authenticator.clone() else {
+            return Box::pin(inner.call(request));
+        };
+
+        Box::pin(async move {
+            match authenticator.authorize(request.uri().path(), request.headers()).await {
+                Ok(credential) => {
+                    if let Some(credential) = credential {
+                        request.extensions_mut().insert(credential);
+                    }
+                    inner.call(request).await
+                }
+                Err(status) => Ok(status.to_http()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::codegen::http::HeaderValue;
+
+    const DEPLOYER_KEY: &str = "deployer.s3cr3t";
+
+    /// Answers with the key id of the request's credential in `x-key-id`
+    #[derive(Clone)]
+    struct Echo;
+
+    impl Service<Request<()>> for Echo {
+        type Response = Response<BoxBody>;
+        type Error = std::convert::Infallible;
+        type Future = std::future::Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: Request<()>) -> Self::Future {
+            let mut response = Response::new(tonic::body::empty_body());
+            if let Some(credential) = request.extensions().get::<Credential>() {
+                response.headers_mut().insert("x-key-id", HeaderValue::from_str(&credential.key_id).unwrap());
+            }
+            std::future::ready(Ok(response))
+        }
+    }
+
+    fn store() -> StaticKeyStore {
+        let mut store = StaticKeyStore::default();
+        store.insert("viewer".to_string(), AccessLevel::Reader, StaticKeyStore::digest("viewer.abc")).unwrap();
+        store.insert("deployer".to_string(), AccessLevel::Operator, StaticKeyStore::digest(DEPLOYER_KEY)).unwrap();
+        store.insert("root".to_string(), AccessLevel::Admin, StaticKeyStore::digest("root.xyz")).unwrap();
+        store
+    }
+
+    fn headers(name: &'static str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_loads_key_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys.json");
+        let digest = hex::encode(StaticKeyStore::digest(DEPLOYER_KEY));
+        std::fs::write(&path, format!(r#"{{"keys": [{{"id": "deployer", "level": "operator", "sha256": "{}"}}]}}"#, digest)).unwrap();
+        assert_eq!(StaticKeyStore::load(&path).unwrap().len(), 1);
+
+        std::fs::write(&path, r#"{"keys": [{"id": "deployer", "level": "operator", "sha256": "abcd"}]}"#).unwrap();
+        assert!(matches!(StaticKeyStore::load(&path), Err(AuthError::InvalidDigest { .. })));
+
+        std::fs::write(
+            &path,
+            format!(
+                r#"{{"keys": [{{"id": "a", "level": "admin", "sha256": "{0}"}}, {{"id": "a", "level": "reader", "sha256": "{0}"}}]}}"#,
+                digest
+            ),
+        )
+        .unwrap();
+        assert!(matches!(StaticKeyStore::load(&path), Err(AuthError::DuplicateKey { .. })));
+    }
+
+    #[test]
+    fn test_method_levels() {
+        let policy = MethodPolicy::default();
+        assert_eq!(policy.required("/vm_service.VmService/ListDots"), Some(AccessLevel::Reader));
+        assert_eq!(policy.required("/vm_service.VmService/DeployDot"), Some(AccessLevel::Operator));
+        assert_eq!(policy.required("/vm_service.VmService/DeleteDot"), Some(AccessLevel::Admin));
+        assert_eq!(policy.required("/vm_service.VmService/HealthCheck"), None);
+        assert_eq!(policy.required("/grpc.reflection.v1alpha.ServerReflection/ServerReflectionInfo"), Some(AccessLevel::Reader));
+
+        let policy = MethodPolicy::new(false, true).with_level("/vm_service.VmService/ExecuteDot", AccessLevel::Reader);
+        assert_eq!(policy.required("/vm_service.VmService/HealthCheck"), Some(AccessLevel::Reader));
+        assert_eq!(policy.required("/grpc.reflection.v1alpha.ServerReflection/ServerReflectionInfo"), None);
+        assert_eq!(policy.required("/vm_service.VmService/ExecuteDot"), Some(AccessLevel::Reader));
+    }
+
+    #[tokio::test]
+    async fn test_authorizes_by_level() {
+        let authenticator = Authenticator::new(Arc::new(store()), MethodPolicy::default());
+
+        let reader = headers("x-api-key", "viewer.abc");
+        assert_eq!(authenticator.authorize("/vm_service.VmService/ListDots", &reader).await.unwrap().unwrap().key_id, "viewer");
+        let status = authenticator.authorize("/vm_service.VmService/DeployDot", &reader).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+        let operator = headers("authorization", &format!("Bearer {}", DEPLOYER_KEY));
+        assert!(authenticator.authorize("/vm_service.VmService/DeployDot", &operator).await.is_ok());
+        assert_eq!(
+            authenticator.authorize("/vm_service.VmService/DeleteDot", &operator).await.unwrap_err().code(),
+            tonic::Code::PermissionDenied
+        );
+
+        let admin = headers("authorization", "Bearer root.xyz");
+        assert!(authenticator.authorize("/vm_service.VmService/DeleteDot", &admin).await.is_ok());
+
+        // Public methods need no key at all
+        assert_eq!(authenticator.authorize("/vm_service.VmService/HealthCheck", &HeaderMap::new()).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_refusals_are_opaque_and_counted() {
+        let authenticator = Authenticator::new(Arc::new(store()), MethodPolicy::default());
+        let method = "/vm_service.VmService/DeployDot";
+
+        let missing = authenticator.authorize(method, &HeaderMap::new()).await.unwrap_err();
+        let wrong_secret = authenticator.authorize(method, &headers("x-api-key", "deployer.guess")).await.unwrap_err();
+        let unknown_id = authenticator.authorize(method, &headers("x-api-key", "nobody.guess")).await.unwrap_err();
+        for status in [&missing, &wrong_secret, &unknown_id] {
+            assert_eq!(status.code(), tonic::Code::Unauthenticated);
+            assert_eq!(status.message(), "Authentication required");
+        }
+        authenticator.authorize(method, &headers("x-api-key", "deployer.guess")).await.unwrap_err();
+        authenticator.authorize(method, &headers("x-api-key", "viewer.abc")).await.unwrap_err();
+
+        assert_eq!(
+            authenticator.rejections(),
+            vec![
+                (ANONYMOUS_KEY_ID.to_string(), 1),
+                ("deployer".to_string(), 2),
+                (UNKNOWN_KEY_ID.to_string(), 1),
+                ("viewer".to_string(), 1),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_layer_answers_refused_calls() {
+        let authenticator = Arc::new(Authenticator::new(Arc::new(store()), MethodPolicy::default()));
+        let mut service = AuthLayer::new(authenticator).layer(Echo);
+
+        let refused = service.call(Request::builder().uri("/vm_service.VmService/DeployDot").body(()).unwrap()).await.unwrap();
+        assert_eq!(refused.headers()["grpc-status"], (tonic::Code::Unauthenticated as i32).to_string().as_str());
+
+        let request = Request::builder().uri("/vm_service.VmService/DeployDot").header(API_KEY_HEADER, DEPLOYER_KEY).body(()).unwrap();
+        let allowed = service.call(request).await.unwrap();
+        assert_eq!(allowed.headers()["x-key-id"], "deployer");
+
+        let mut open = AuthLayer::disabled().layer(Echo);
+        let allowed = open.call(Request::builder().uri("/vm_service.VmService/DeleteDot").body(()).unwrap()).await.unwrap();
+        assert!(allowed.headers().get("x-key-id").is_none());
+    }
+}
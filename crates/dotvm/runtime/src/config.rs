@@ -16,8 +16,25 @@
 
 //! Runtime configuration for gRPC server
 
+use std::collections::HashSet;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
+
+use dotdb_core::document::replication::DEFAULT_FEED_RETENTION;
+use dotvm_core::bytecode::VmArchitecture;
+use dotvm_runtime::auth::AuthConfig;
+use dotvm_runtime::rollback::operation::DEFAULT_CHECKPOINTS_PER_CATEGORY;
+
+use crate::services::abi::events::EventSchemaStrictness;
+use crate::services::dots::architecture::ArchitectureConfig;
+use crate::services::dots::memo::DEFAULT_RESULT_CACHE_BYTES;
+use crate::services::dots::recording::RecordingConfig;
+use crate::services::dots::upload::DEFAULT_MAX_ARTIFACT_BYTES;
+use crate::services::logs::LogRetention;
+use crate::services::metrics::latency::{LatencyConfig, validate_buckets};
+use crate::tls::TlsConfig;
 
 #[derive(Debug, Clone)]
 pub struct RuntimeConfig {
@@ -26,6 +43,42 @@ pub struct RuntimeConfig {
     pub enable_health_check: bool,
     pub max_connections: u32,
     pub connection_timeout_ms: u64,
+    /// How long a replaced dot version stays available for rollback once its executions finish
+    pub dot_drain_grace_period_secs: u64,
+    /// Memory the memoized results of deterministic dots may hold
+    pub dot_result_cache_bytes: usize,
+    /// Checkpoints kept for each kind of risky operation, such as redeploys
+    pub checkpoints_per_category: usize,
+    /// Largest bytecode a streamed dot deployment may carry
+    pub max_artifact_bytes: u64,
+    /// Storage file of the node's database engine; without one, storage admin calls are unavailable
+    pub storage_path: Option<PathBuf>,
+    /// Serve TLS instead of plaintext
+    pub tls: Option<TlsConfig>,
+    /// Require API keys on gRPC calls; without it every caller is trusted
+    pub auth: Option<AuthConfig>,
+    /// API key a replica presents to its primary
+    pub replication_api_key: Option<String>,
+    /// Directory of the node's document store; without one, documents and dot state are ephemeral and live in memory
+    pub document_path: Option<PathBuf>,
+    /// Bytes an in-memory document store may hold before refusing writes; ignored with a `document_path`
+    pub document_memory_budget: Option<usize>,
+    /// Follow the primary at this address as a read-only replica instead of accepting writes
+    pub replica_of: Option<String>,
+    /// Name this node reports to its primary; defaults to the bind address
+    pub replica_id: Option<String>,
+    /// Document changes a primary keeps for replicas to catch up from
+    pub change_feed_retention: usize,
+    /// What happens to emitted dot events that do not match the schema in their dot's ABI
+    pub event_schema_strictness: EventSchemaStrictness,
+    /// How long and how many centralized log records are kept
+    pub log_retention: LogRetention,
+    /// Which executions are recorded for replay and what their traces may hold
+    pub recording: RecordingConfig,
+    /// Bucket bounds of the per-dot latency histograms and how long idle dots keep theirs
+    pub latency: LatencyConfig,
+    /// Architectures dots may target, and the one source is compiled for by default
+    pub architectures: ArchitectureConfig,
 }
 
 impl Default for RuntimeConfig {
@@ -36,6 +89,24 @@ impl Default for RuntimeConfig {
             enable_health_check: true,
             max_connections: 1000,
             connection_timeout_ms: 30000,
+            dot_drain_grace_period_secs: 300,
+            dot_result_cache_bytes: DEFAULT_RESULT_CACHE_BYTES,
+            checkpoints_per_category: DEFAULT_CHECKPOINTS_PER_CATEGORY,
+            max_artifact_bytes: DEFAULT_MAX_ARTIFACT_BYTES,
+            storage_path: None,
+            tls: None,
+            auth: None,
+            replication_api_key: None,
+            document_path: None,
+            document_memory_budget: None,
+            replica_of: None,
+            replica_id: None,
+            change_feed_retention: DEFAULT_FEED_RETENTION,
+            event_schema_strictness: EventSchemaStrictness::default(),
+            log_retention: LogRetention::default(),
+            recording: RecordingConfig::default(),
+            latency: LatencyConfig::default(),
+            architectures: ArchitectureConfig::default(),
         }
     }
 }
@@ -65,6 +136,139 @@ impl RuntimeConfig {
             }
         }
 
+        if let Ok(grace_str) = std::env::var("DOT_DRAIN_GRACE_PERIOD_SECS") {
+            if let Ok(grace) = grace_str.parse::<u64>() {
+                config.dot_drain_grace_period_secs = grace;
+            }
+        }
+
+        if let Ok(cache_str) = std::env::var("DOT_RESULT_CACHE_BYTES") {
+            if let Ok(bytes) = cache_str.parse::<usize>() {
+                config.dot_result_cache_bytes = bytes;
+            }
+        }
+
+        if let Ok(keep_str) = std::env::var("DOTVM_CHECKPOINTS_PER_CATEGORY") {
+            if let Ok(keep) = keep_str.parse::<usize>() {
+                config.checkpoints_per_category = keep;
+            }
+        }
+
+        if let Ok(max_str) = std::env::var("DOTVM_MAX_ARTIFACT_BYTES") {
+            if let Ok(max_bytes) = max_str.parse::<u64>() {
+                config.max_artifact_bytes = max_bytes;
+            }
+        }
+
+        if let Ok(path) = std::env::var("DOTDB_STORAGE_PATH")
+            && !path.is_empty()
+        {
+            config.storage_path = Some(PathBuf::from(path));
+        }
+
+        config.tls = tls_from_env();
+        config.auth = auth_from_env();
+
+        if let Ok(key) = std::env::var("DOTVM_REPLICATION_API_KEY")
+            && !key.is_empty()
+        {
+            config.replication_api_key = Some(key);
+        }
+
+        if let Ok(path) = std::env::var("DOTDB_DOCUMENT_PATH")
+            && !path.is_empty()
+        {
+            config.document_path = Some(PathBuf::from(path));
+        }
+
+        if let Ok(budget_str) = std::env::var("DOTDB_DOCUMENT_MEMORY_BUDGET") {
+            if let Ok(budget) = budget_str.parse::<usize>() {
+                config.document_memory_budget = Some(budget);
+            }
+        }
+
+        if let Ok(primary) = std::env::var("DOTDB_REPLICA_OF")
+            && !primary.is_empty()
+        {
+            config.replica_of = Some(primary);
+        }
+
+        if let Ok(replica_id) = std::env::var("DOTDB_REPLICA_ID")
+            && !replica_id.is_empty()
+        {
+            config.replica_id = Some(replica_id);
+        }
+
+        if let Ok(retention_str) = std::env::var("DOTDB_CHANGE_FEED_RETENTION") {
+            if let Ok(retention) = retention_str.parse::<usize>() {
+                config.change_feed_retention = retention;
+            }
+        }
+
+        if let Ok(strictness_str) = std::env::var("DOTVM_EVENT_SCHEMA_STRICTNESS") {
+            match strictness_str.parse::<EventSchemaStrictness>() {
+                Ok(strictness) => config.event_schema_strictness = strictness,
+                Err(e) => eprintln!("Warning: Invalid DOTVM_EVENT_SCHEMA_STRICTNESS: {}, using default", e),
+            }
+        }
+
+        if let Ok(age_str) = std::env::var("DOTVM_LOG_RETENTION_SECS") {
+            if let Ok(secs) = age_str.parse::<u64>() {
+                config.log_retention.max_age = Duration::from_secs(secs);
+            }
+        }
+
+        if let Ok(entries_str) = std::env::var("DOTVM_LOG_RETENTION_ENTRIES") {
+            if let Ok(entries) = entries_str.parse::<usize>() {
+                config.log_retention.max_entries = entries;
+            }
+        }
+
+        if let Ok(dots) = std::env::var("DOTVM_RECORD_DOTS") {
+            config.recording.dots = comma_list(&dots);
+        }
+
+        if let Ok(max_str) = std::env::var("DOTVM_TRACE_MAX_BYTES") {
+            if let Ok(max) = max_str.parse::<usize>() {
+                config.recording.max_trace_bytes = max;
+            }
+        }
+
+        if let Ok(fields) = std::env::var("DOTVM_TRACE_MASKED_INPUTS") {
+            config.recording.masked_inputs = comma_list(&fields);
+        }
+
+        if let Ok(buckets_str) = std::env::var("DOTVM_LATENCY_BUCKETS_MS") {
+            match bucket_bounds(&buckets_str) {
+                Ok(buckets) => config.latency.buckets_ms = buckets,
+                Err(e) => eprintln!("Warning: Invalid DOTVM_LATENCY_BUCKETS_MS: {}, using default", e),
+            }
+        }
+
+        if let Ok(ttl_str) = std::env::var("DOTVM_LATENCY_IDLE_TTL_SECS") {
+            if let Ok(secs) = ttl_str.parse::<u64>() {
+                config.latency.idle_ttl = Duration::from_secs(secs);
+            }
+        }
+
+        if let Ok(supported_str) = std::env::var("DOTVM_SUPPORTED_ARCHITECTURES") {
+            match architecture_list(&supported_str) {
+                Ok(supported) => config.architectures.supported = supported,
+                Err(e) => eprintln!("Warning: Invalid DOTVM_SUPPORTED_ARCHITECTURES: {}, using default", e),
+            }
+        }
+
+        if let Ok(default_str) = std::env::var("DOTVM_DEFAULT_ARCHITECTURE") {
+            match default_str.parse() {
+                Ok(default) => config.architectures.default = default,
+                Err(e) => eprintln!("Warning: Invalid DOTVM_DEFAULT_ARCHITECTURE: {}, using default", e),
+            }
+        }
+        if !config.architectures.is_supported(config.architectures.default) {
+            eprintln!("Warning: Default architecture {} is not supported, adding it to the supported architectures", config.architectures.default);
+            config.architectures.supported.push(config.architectures.default);
+        }
+
         config
     }
 
@@ -87,3 +291,65 @@ impl RuntimeConfig {
         format!("{}:{}", host, self.bind_address.port()).parse().unwrap_or(self.bind_address)
     }
 }
+
+/// Histogram bucket bounds from a comma-separated list of milliseconds
+fn bucket_bounds(value: &str) -> Result<Vec<f64>, String> {
+    let bounds = value.split(',').map(|bound| bound.trim().parse::<f64>()).collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?;
+    validate_buckets(&bounds).map_err(|e| e.to_string())?;
+    Ok(bounds)
+}
+
+/// Distinct architectures from a comma-separated list of names, e.g. `arch64,arch128`
+fn architecture_list(value: &str) -> Result<Vec<VmArchitecture>, String> {
+    let mut architectures = Vec::new();
+    for name in value.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        let architecture = name.parse::<VmArchitecture>()?;
+        if !architectures.contains(&architecture) {
+            architectures.push(architecture);
+        }
+    }
+    if architectures.is_empty() {
+        return Err("no architectures listed".to_string());
+    }
+    Ok(architectures)
+}
+
+/// Non-empty, trimmed entries of a comma-separated list
+fn comma_list(value: &str) -> HashSet<String> {
+    value.split(',').map(str::trim).filter(|entry| !entry.is_empty()).map(str::to_string).collect()
+}
+
+/// TLS settings from `DOTVM_TLS_*`; both a certificate and a key are needed to turn TLS on
+fn tls_from_env() -> Option<TlsConfig> {
+    let path = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty()).map(PathBuf::from);
+
+    let (cert_path, key_path) = match (path("DOTVM_TLS_CERT"), path("DOTVM_TLS_KEY")) {
+        (Some(cert), Some(key)) => (cert, key),
+        (None, None) => return None,
+        _ => {
+            eprintln!("Warning: DOTVM_TLS_CERT and DOTVM_TLS_KEY must be set together, serving plaintext");
+            return None;
+        }
+    };
+
+    let reload_interval_secs = std::env::var("DOTVM_TLS_RELOAD_INTERVAL_SECS").ok().and_then(|secs| secs.parse::<u64>().ok()).filter(|&secs| secs > 0);
+    Some(TlsConfig {
+        cert_path,
+        key_path,
+        client_ca_path: path("DOTVM_TLS_CLIENT_CA"),
+        require_client_auth: std::env::var("DOTVM_TLS_REQUIRE_CLIENT_AUTH").is_ok_and(|value| value.parse().unwrap_or(false)),
+        reload_interval: Duration::from_secs(reload_interval_secs.unwrap_or(60)),
+    })
+}
+
+/// API key settings from `DOTVM_AUTH_*`; a key file turns authentication on
+fn auth_from_env() -> Option<AuthConfig> {
+    let keys_path = std::env::var("DOTVM_AUTH_KEYS_FILE").ok().filter(|path| !path.is_empty())?;
+    let flag = |name: &str, default: bool| std::env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default);
+
+    Some(AuthConfig {
+        keys_path: PathBuf::from(keys_path),
+        public_health_check: flag("DOTVM_AUTH_PUBLIC_HEALTH_CHECK", true),
+        public_reflection: flag("DOTVM_AUTH_PUBLIC_REFLECTION", false),
+    })
+}
@@ -0,0 +1,280 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Event bus with persistent subscriptions
+//!
+//! Combines the in-process [`EventDispatcher`] with the persistent [`EventLog`]:
+//! durable events are appended to the log and fanned out to live subscribers,
+//! and a subscriber can attach at any retained sequence number to replay what
+//! it missed before switching over to live delivery. Every subscriber sees
+//! records in sequence order, which also preserves the order of each dot's
+//! events.
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use dotdb_core::state::db_interface::{Database, DbResult};
+use futures::Stream;
+use tokio::sync::broadcast;
+use tracing::warn;
+
+use crate::events::dispatcher::EventDispatcher;
+use crate::events::lib::Event;
+use crate::events::log::{DurableEvent, EventLog, EventRecord, RetentionPolicy};
+use crate::events::queue::EventQueue;
+
+/// Stream of durable events delivered to a subscriber
+pub type EventSubscription = Pin<Box<dyn Stream<Item = DbResult<EventRecord>> + Send>>;
+
+/// Default number of records buffered for live subscribers
+pub const DEFAULT_LIVE_BUFFER: usize = 1024;
+
+/// Number of records read from the log per replay step
+const REPLAY_BATCH_SIZE: usize = 256;
+
+/// Event bus that persists durable events and supports replaying subscriptions
+pub struct EventBus {
+    dispatcher: Arc<EventDispatcher>,
+    log: Arc<EventLog>,
+    live: broadcast::Sender<EventRecord>,
+    /// Serializes appends so live delivery follows sequence order
+    append_lock: Mutex<()>,
+}
+
+impl EventBus {
+    /// Create an event bus over an existing dispatcher and log
+    ///
+    /// `live_buffer` bounds how far a live subscriber may fall behind before it
+    /// switches back to reading from the log.
+    pub fn new(dispatcher: Arc<EventDispatcher>, log: Arc<EventLog>, live_buffer: usize) -> Self {
+        let (live, _) = broadcast::channel(live_buffer.max(1));
+        Self {
+            dispatcher,
+            log,
+            live,
+            append_lock: Mutex::new(()),
+        }
+    }
+
+    /// Create an event bus backed by an in-memory database
+    pub fn in_memory(retention: RetentionPolicy) -> DbResult<Self> {
+        let log = EventLog::open(Arc::new(Database::new_in_memory()?), retention)?;
+        let dispatcher = EventDispatcher::new(Arc::new(Mutex::new(EventQueue::new())));
+        Ok(Self::new(Arc::new(dispatcher), Arc::new(log), DEFAULT_LIVE_BUFFER))
+    }
+
+    /// Publish an event to in-process handlers, appending it to the log if it is durable
+    ///
+    /// Returns the assigned sequence number for durable events.
+    pub fn publish<T: Event + 'static>(&self, event: T) -> DbResult<Option<u64>> {
+        let sequence = match event.to_durable() {
+            Some(durable) => Some(self.publish_durable(durable)?.sequence),
+            None => None,
+        };
+
+        self.dispatcher.publish(event);
+        Ok(sequence)
+    }
+
+    /// Append a durable event to the log and deliver it to live subscribers
+    pub fn publish_durable(&self, event: DurableEvent) -> DbResult<EventRecord> {
+        let _guard = self.append_lock.lock().unwrap_or_else(|e| e.into_inner());
+
+        let record = self.log.append(event)?;
+        // No receivers is fine: subscribers that attach later replay from the log
+        let _ = self.live.send(record.clone());
+        Ok(record)
+    }
+
+    /// Subscribe starting at `from_sequence`
+    ///
+    /// Retained records from `from_sequence` onwards are replayed first, then
+    /// the subscription continues with live events. Records already trimmed by
+    /// retention are skipped. Only records accepted by `filter` are delivered.
+    pub fn subscribe_from<F>(&self, from_sequence: u64, filter: F) -> EventSubscription
+    where
+        F: Fn(&EventRecord) -> bool + Send + Sync + 'static,
+    {
+        let state = SubscriptionState {
+            log: Arc::clone(&self.log),
+            receiver: self.live.subscribe(),
+            next: from_sequence.max(1),
+            backlog: VecDeque::new(),
+            filter,
+        };
+
+        Box::pin(futures::stream::unfold(state, SubscriptionState::next_record))
+    }
+
+    /// Subscribe to events published from now on
+    pub fn subscribe_live<F>(&self, filter: F) -> EventSubscription
+    where
+        F: Fn(&EventRecord) -> bool + Send + Sync + 'static,
+    {
+        let _guard = self.append_lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.subscribe_from(self.log.next_sequence(), filter)
+    }
+
+    /// The in-process dispatcher
+    pub fn dispatcher(&self) -> &Arc<EventDispatcher> {
+        &self.dispatcher
+    }
+
+    /// The persistent event log
+    pub fn log(&self) -> &Arc<EventLog> {
+        &self.log
+    }
+
+    /// Periodically trim the log so age-based retention applies without new appends
+    pub fn start_retention_task(self: &Arc<Self>, every: Duration) -> tokio::task::JoinHandle<()> {
+        let bus = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(every);
+            loop {
+                interval.tick().await;
+                if let Err(e) = bus.log.apply_retention() {
+                    warn!("Event log retention failed: {}", e);
+                }
+            }
+        })
+    }
+}
+
+/// Replay-then-live delivery state for one subscriber
+struct SubscriptionState<F> {
+    log: Arc<EventLog>,
+    receiver: broadcast::Receiver<EventRecord>,
+    /// Next sequence number to deliver
+    next: u64,
+    /// Records read but not yet delivered
+    backlog: VecDeque<EventRecord>,
+    filter: F,
+}
+
+impl<F> SubscriptionState<F>
+where
+    F: Fn(&EventRecord) -> bool + Send + Sync + 'static,
+{
+    async fn next_record(mut self) -> Option<(DbResult<EventRecord>, Self)> {
+        loop {
+            while let Some(record) = self.backlog.pop_front() {
+                self.next = record.sequence + 1;
+                if (self.filter)(&record) {
+                    return Some((Ok(record), self));
+                }
+            }
+
+            // Catch up from the log whenever live delivery is behind it
+            if self.next < self.log.next_sequence() {
+                match self.log.read_from(self.next, REPLAY_BATCH_SIZE) {
+                    Ok(records) if records.is_empty() => self.next = self.log.first_sequence().max(self.next + 1),
+                    Ok(records) => self.backlog.extend(records),
+                    Err(e) => return Some((Err(e), self)),
+                }
+                continue;
+            }
+
+            match self.receiver.recv().await {
+                Ok(record) if record.sequence == self.next => self.backlog.push_back(record),
+                // Older records were already replayed; newer ones are picked up from the log
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    #[derive(Debug)]
+    struct Deployed {
+        dot_id: String,
+    }
+
+    impl Event for Deployed {
+        fn to_durable(&self) -> Option<DurableEvent> {
+            Some(DurableEvent::new(self.dot_id.clone(), "deployed", vec![]))
+        }
+    }
+
+    #[derive(Debug)]
+    struct Transient;
+
+    impl Event for Transient {}
+
+    #[tokio::test]
+    async fn test_only_durable_events_are_logged() {
+        let bus = EventBus::in_memory(RetentionPolicy::unbounded()).unwrap();
+
+        assert_eq!(bus.publish(Deployed { dot_id: "dot1".into() }).unwrap(), Some(1));
+        assert_eq!(bus.publish(Transient).unwrap(), None);
+        assert_eq!(bus.log().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_from_replays_then_goes_live() {
+        let bus = EventBus::in_memory(RetentionPolicy::unbounded()).unwrap();
+        for dot in ["dot1", "dot2", "dot1"] {
+            bus.publish(Deployed { dot_id: dot.into() }).unwrap();
+        }
+
+        let mut subscription = bus.subscribe_from(2, |_| true);
+        bus.publish(Deployed { dot_id: "dot2".into() }).unwrap();
+
+        let mut sequences = Vec::new();
+        for _ in 0..3 {
+            sequences.push(subscription.next().await.unwrap().unwrap().sequence);
+        }
+        assert_eq!(sequences, vec![2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_subscription_filter_preserves_per_dot_order() {
+        let bus = EventBus::in_memory(RetentionPolicy::unbounded()).unwrap();
+        let mut subscription = bus.subscribe_from(1, |record| record.event.dot_id == "dot1");
+
+        for dot in ["dot1", "dot2", "dot1", "dot2", "dot1"] {
+            bus.publish(Deployed { dot_id: dot.into() }).unwrap();
+        }
+
+        let mut sequences = Vec::new();
+        for _ in 0..3 {
+            sequences.push(subscription.next().await.unwrap().unwrap().sequence);
+        }
+        assert_eq!(sequences, vec![1, 3, 5]);
+    }
+
+    #[tokio::test]
+    async fn test_lagging_subscriber_recovers_from_log() {
+        let log = EventLog::open(Arc::new(Database::new_in_memory().unwrap()), RetentionPolicy::unbounded()).unwrap();
+        let dispatcher = EventDispatcher::new(Arc::new(Mutex::new(EventQueue::new())));
+        let bus = EventBus::new(Arc::new(dispatcher), Arc::new(log), 1);
+
+        let mut subscription = bus.subscribe_live(|_| true);
+        for _ in 0..10 {
+            bus.publish(Deployed { dot_id: "dot1".into() }).unwrap();
+        }
+
+        for expected in 1..=10 {
+            assert_eq!(subscription.next().await.unwrap().unwrap().sequence, expected);
+        }
+    }
+}
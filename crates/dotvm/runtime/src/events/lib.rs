@@ -20,6 +20,8 @@ use std::hash::Hash;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
+use crate::events::log::DurableEvent;
+
 /// The priority level for events
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
 pub enum Priority {
@@ -36,6 +38,14 @@ pub trait Event: Any + Send + Sync + Debug {
     fn priority(&self) -> Priority {
         Priority::default()
     }
+
+    /// Returns the persisted form of this event if it is durable
+    ///
+    /// Durable events are appended to the event log by the [`EventBus`](crate::events::EventBus)
+    /// so that subscribers can replay them; the default keeps events in-process only.
+    fn to_durable(&self) -> Option<DurableEvent> {
+        None
+    }
 }
 
 /// A unique identifier for an event handler
@@ -0,0 +1,356 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Persistent event log
+//!
+//! Durable events are appended to a dotdb-backed log under monotonically
+//! increasing sequence numbers so subscribers can replay what they missed.
+//! The log is trimmed from the oldest end according to a [`RetentionPolicy`].
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use dotdb_core::state::db_interface::{BatchOp, DatabaseInterface, DbError, DbResult};
+use serde::{Deserialize, Serialize};
+
+/// Key prefix for log records, followed by the big-endian sequence number
+const RECORD_PREFIX: &[u8] = b"events/log/";
+/// Key holding the log bounds
+const META_KEY: &[u8] = b"events/meta";
+
+/// An event that should be persisted to the event log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DurableEvent {
+    /// Dot the event belongs to
+    pub dot_id: String,
+    /// Event type name
+    pub event_type: String,
+    /// Opaque event payload
+    pub payload: Vec<u8>,
+    /// Additional key/value metadata
+    pub metadata: HashMap<String, String>,
+}
+
+impl DurableEvent {
+    /// Create a durable event
+    pub fn new(dot_id: impl Into<String>, event_type: impl Into<String>, payload: Vec<u8>) -> Self {
+        Self {
+            dot_id: dot_id.into(),
+            event_type: event_type.into(),
+            payload,
+            metadata: HashMap::new(),
+        }
+    }
+
+    /// Attach a metadata entry
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// A durable event as stored in the log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventRecord {
+    /// Position in the log, starting at 1
+    pub sequence: u64,
+    /// Append time in milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    /// The persisted event
+    pub event: DurableEvent,
+}
+
+/// Bounds on how much history the event log keeps
+///
+/// Records are removed oldest first once any configured limit is exceeded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Maximum age of a record
+    pub max_age: Option<Duration>,
+    /// Maximum total size of all records in bytes
+    pub max_bytes: Option<u64>,
+    /// Maximum number of records
+    pub max_events: Option<u64>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_age: Some(Duration::from_secs(24 * 60 * 60)),
+            max_bytes: Some(256 * 1024 * 1024),
+            max_events: None,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// Keep every record forever
+    pub fn unbounded() -> Self {
+        Self {
+            max_age: None,
+            max_bytes: None,
+            max_events: None,
+        }
+    }
+}
+
+/// Bounds of the log, persisted alongside the records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct LogBounds {
+    /// Oldest retained sequence number
+    first: u64,
+    /// Sequence number the next append receives
+    next: u64,
+    /// Total size of retained records in bytes
+    bytes: u64,
+}
+
+impl Default for LogBounds {
+    fn default() -> Self {
+        Self { first: 1, next: 1, bytes: 0 }
+    }
+}
+
+/// Append-only, dotdb-backed log of durable events
+pub struct EventLog {
+    db: Arc<dyn DatabaseInterface>,
+    retention: RetentionPolicy,
+    bounds: Mutex<LogBounds>,
+}
+
+impl EventLog {
+    /// Open the event log stored in `db`, resuming after the last persisted record
+    pub fn open(db: Arc<dyn DatabaseInterface>, retention: RetentionPolicy) -> DbResult<Self> {
+        let bounds = match db.get(META_KEY)? {
+            Some(bytes) => serde_json::from_slice(&bytes).map_err(|e| DbError::Serialization(e.to_string()))?,
+            None => LogBounds::default(),
+        };
+
+        Ok(Self {
+            db,
+            retention,
+            bounds: Mutex::new(bounds),
+        })
+    }
+
+    /// Append an event, returning the stored record
+    pub fn append(&self, event: DurableEvent) -> DbResult<EventRecord> {
+        let mut bounds = self.bounds.lock().unwrap_or_else(|e| e.into_inner());
+
+        let record = EventRecord {
+            sequence: bounds.next,
+            timestamp_ms: now_ms(),
+            event,
+        };
+        let value = serde_json::to_vec(&record).map_err(|e| DbError::Serialization(e.to_string()))?;
+
+        let mut updated = *bounds;
+        updated.next += 1;
+        updated.bytes += value.len() as u64;
+        self.db.batch(vec![
+            BatchOp::Put {
+                key: record_key(record.sequence),
+                value,
+            },
+            Self::bounds_op(&updated)?,
+        ])?;
+        *bounds = updated;
+
+        self.enforce_retention(&mut bounds, now_ms())?;
+        Ok(record)
+    }
+
+    /// Read up to `limit` retained records starting at `from`, in sequence order
+    ///
+    /// Records that have already been trimmed by retention are skipped.
+    pub fn read_from(&self, from: u64, limit: usize) -> DbResult<Vec<EventRecord>> {
+        let bounds = *self.bounds.lock().unwrap_or_else(|e| e.into_inner());
+        let start = from.max(bounds.first);
+        let end = bounds.next.min(start.saturating_add(limit as u64));
+
+        let mut records = Vec::with_capacity(end.saturating_sub(start) as usize);
+        for sequence in start..end {
+            if let Some(bytes) = self.db.get(&record_key(sequence))? {
+                records.push(serde_json::from_slice(&bytes).map_err(|e| DbError::Serialization(e.to_string()))?);
+            }
+        }
+        Ok(records)
+    }
+
+    /// Oldest retained sequence number
+    pub fn first_sequence(&self) -> u64 {
+        self.bounds.lock().unwrap_or_else(|e| e.into_inner()).first
+    }
+
+    /// Sequence number the next appended event will receive
+    pub fn next_sequence(&self) -> u64 {
+        self.bounds.lock().unwrap_or_else(|e| e.into_inner()).next
+    }
+
+    /// Number of retained records
+    pub fn len(&self) -> u64 {
+        let bounds = self.bounds.lock().unwrap_or_else(|e| e.into_inner());
+        bounds.next - bounds.first
+    }
+
+    /// Whether the log holds no records
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Total size of retained records in bytes
+    pub fn size_bytes(&self) -> u64 {
+        self.bounds.lock().unwrap_or_else(|e| e.into_inner()).bytes
+    }
+
+    /// The retention policy in effect
+    pub fn retention(&self) -> &RetentionPolicy {
+        &self.retention
+    }
+
+    /// Trim records that exceed the retention policy, returning how many were removed
+    pub fn apply_retention(&self) -> DbResult<u64> {
+        let mut bounds = self.bounds.lock().unwrap_or_else(|e| e.into_inner());
+        self.enforce_retention(&mut bounds, now_ms())
+    }
+
+    fn enforce_retention(&self, bounds: &mut LogBounds, now_ms: u64) -> DbResult<u64> {
+        let mut removed = 0;
+
+        while bounds.first < bounds.next {
+            let over_count = self.retention.max_events.is_some_and(|max| bounds.next - bounds.first > max);
+            let over_size = self.retention.max_bytes.is_some_and(|max| bounds.bytes > max);
+
+            let key = record_key(bounds.first);
+            let Some(bytes) = self.db.get(&key)? else {
+                // Already gone; just move the lower bound past it
+                bounds.first += 1;
+                continue;
+            };
+
+            let expired = match self.retention.max_age {
+                Some(max_age) => {
+                    let record: EventRecord = serde_json::from_slice(&bytes).map_err(|e| DbError::Serialization(e.to_string()))?;
+                    now_ms.saturating_sub(record.timestamp_ms) > max_age.as_millis() as u64
+                }
+                None => false,
+            };
+
+            if !(over_count || over_size || expired) {
+                break;
+            }
+
+            let mut updated = *bounds;
+            updated.first += 1;
+            updated.bytes = updated.bytes.saturating_sub(bytes.len() as u64);
+            self.db.batch(vec![BatchOp::Delete { key }, Self::bounds_op(&updated)?])?;
+            *bounds = updated;
+            removed += 1;
+        }
+
+        Ok(removed)
+    }
+
+    fn bounds_op(bounds: &LogBounds) -> DbResult<BatchOp> {
+        Ok(BatchOp::Put {
+            key: META_KEY.to_vec(),
+            value: serde_json::to_vec(bounds).map_err(|e| DbError::Serialization(e.to_string()))?,
+        })
+    }
+}
+
+fn record_key(sequence: u64) -> Vec<u8> {
+    let mut key = Vec::with_capacity(RECORD_PREFIX.len() + 8);
+    key.extend_from_slice(RECORD_PREFIX);
+    key.extend_from_slice(&sequence.to_be_bytes());
+    key
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dotdb_core::state::db_interface::Database;
+
+    fn in_memory_log(retention: RetentionPolicy) -> EventLog {
+        EventLog::open(Arc::new(Database::new_in_memory().unwrap()), retention).unwrap()
+    }
+
+    #[test]
+    fn test_append_assigns_increasing_sequences() {
+        let log = in_memory_log(RetentionPolicy::unbounded());
+
+        let first = log.append(DurableEvent::new("dot1", "deployed", vec![1])).unwrap();
+        let second = log.append(DurableEvent::new("dot1", "executed", vec![2])).unwrap();
+
+        assert_eq!(first.sequence, 1);
+        assert_eq!(second.sequence, 2);
+        assert_eq!(log.next_sequence(), 3);
+
+        let records = log.read_from(2, 10).unwrap();
+        assert_eq!(records, vec![second]);
+    }
+
+    #[test]
+    fn test_log_survives_reopen() {
+        let db: Arc<dyn DatabaseInterface> = Arc::new(Database::new_in_memory().unwrap());
+        let log = EventLog::open(db.clone(), RetentionPolicy::unbounded()).unwrap();
+        log.append(DurableEvent::new("dot1", "deployed", vec![])).unwrap();
+        drop(log);
+
+        let reopened = EventLog::open(db, RetentionPolicy::unbounded()).unwrap();
+        assert_eq!(reopened.next_sequence(), 2);
+        assert_eq!(reopened.read_from(1, 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_retention_by_count_and_size() {
+        let log = in_memory_log(RetentionPolicy {
+            max_events: Some(2),
+            ..RetentionPolicy::unbounded()
+        });
+        for i in 0..5u8 {
+            log.append(DurableEvent::new("dot1", "tick", vec![i])).unwrap();
+        }
+        assert_eq!(log.first_sequence(), 4);
+        assert_eq!(log.read_from(1, 10).unwrap().iter().map(|r| r.sequence).collect::<Vec<_>>(), vec![4, 5]);
+
+        let log = in_memory_log(RetentionPolicy {
+            max_bytes: Some(1),
+            ..RetentionPolicy::unbounded()
+        });
+        log.append(DurableEvent::new("dot1", "tick", vec![0; 64])).unwrap();
+        assert!(log.is_empty());
+        assert_eq!(log.size_bytes(), 0);
+    }
+
+    #[test]
+    fn test_retention_by_age() {
+        let log = in_memory_log(RetentionPolicy {
+            max_age: Some(Duration::from_millis(1)),
+            ..RetentionPolicy::unbounded()
+        });
+        log.append(DurableEvent::new("dot1", "tick", vec![])).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+
+        assert_eq!(log.apply_retention().unwrap(), 1);
+        assert!(log.is_empty());
+    }
+}
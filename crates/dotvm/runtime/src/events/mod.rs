@@ -15,15 +15,19 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 // Public modules
+pub mod bus;
 pub mod dispatcher;
 pub mod filter;
 pub mod lib;
+pub mod log;
 pub mod prioritization;
 pub mod queue;
 
 // Re-export primary types for easier access
+pub use bus::{EventBus, EventSubscription};
 pub use dispatcher::EventDispatcher;
 pub use filter::{FilterManager, FilterResult};
 pub use lib::{Event, HandlerId, Priority};
+pub use log::{DurableEvent, EventLog, EventRecord, RetentionPolicy};
 pub use prioritization::PriorityManager;
 pub use queue::EventQueue;
@@ -14,11 +14,21 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use tonic::service::interceptor::InterceptedService;
 use tonic::transport::Server;
 use tonic::{Request, Response, Status};
 
 mod config;
+mod tls;
 use config::RuntimeConfig;
+use dotdb_core::document::{ChangeFeed, ChangeFeedStorage, CollectionManager, DocumentStore, Replica, create_in_memory_collection_manager, create_in_memory_collection_manager_with_budget};
+use dotdb_core::state::db_interface::{Database, DbConfig};
+use dotdb_core::statistics::{STATISTICS_FILE, StatisticsCollector, StatisticsConfig};
+use dotdb_core::storage_engine::{StorageConfig, StorageEngine};
+use dotvm_core::vm::replay::TraceStore;
+use dotvm_runtime::auth::{AuthLayer, Authenticator, MethodPolicy, StaticKeyStore};
+use dotvm_runtime::rollback::OperationCheckpoints;
+use tls::ReloadableTls;
 
 // Basic proto imports
 mod proto {
@@ -45,7 +55,18 @@ use proto::runtime_server::{Runtime, RuntimeServer};
 use proto::vm_service::vm_service_server::{VmService, VmServiceServer};
 
 mod services;
-use services::{ClusterServiceImpl, DatabaseServiceImpl};
+use services::abi::registry::ABI_NAMESPACE;
+use services::dots::permissions::PermissionEvaluator;
+use services::dots::recording::ExecutionRecorder;
+use services::dots::state::{DEFAULT_MAX_VERSIONS_PER_DOT, DOT_STATE_DIR, DotStateStore};
+use services::logs::{LogStore, LogsService};
+use services::metrics::latency::LatencyHistograms;
+use services::replication::{ReplicaFollower, ReplicationPrimary, ReplicationRole};
+use services::streaming::{DotEventBroadcaster, dot_events};
+use services::vm_management::service::architecture_infos;
+use services::{AbiService, ClusterServiceImpl, DatabaseServiceImpl, DotsService, MetricsService};
+use std::sync::Arc;
+use std::time::Duration;
 
 // Simple working runtime service
 #[derive(Debug, Default)]
@@ -65,45 +86,54 @@ impl Runtime for SimpleRuntimeService {
 }
 
 // Basic VM service implementation - simplified and working
-#[derive(Debug, Default)]
-struct VmServiceImpl;
+struct VmServiceImpl {
+    // Dot lifecycle and state calls go to the dots service
+    dots: Arc<DotsService>,
+    // ABI generation and the ABI registry go to the ABI service
+    abi: Arc<AbiService>,
+    // Dot event subscriptions are served from the persistent event bus
+    events: Arc<DotEventBroadcaster>,
+    // Per-dot memory metrics and tracking settings go to the metrics service
+    metrics: Arc<MetricsService>,
+    // Log queries and shipped gateway logs go to the centralized log store
+    logs: Arc<LogsService>,
+}
+
+impl Default for VmServiceImpl {
+    fn default() -> Self {
+        Self {
+            dots: Arc::new(DotsService::new()),
+            abi: Arc::new(AbiService::new()),
+            events: Arc::new(DotEventBroadcaster::new()),
+            metrics: Arc::new(MetricsService::new()),
+            logs: Arc::new(LogsService::new()),
+        }
+    }
+}
 
 #[tonic::async_trait]
 impl VmService for VmServiceImpl {
     async fn get_architectures(&self, _request: Request<proto::vm_service::GetArchitecturesRequest>) -> Result<Response<proto::vm_service::GetArchitecturesResponse>, Status> {
         println!("GetArchitectures called");
+        // The architectures dots are accepted for, as configured for the dots service
         let response = proto::vm_service::GetArchitecturesResponse {
-            architectures: vec![
-                proto::vm_service::ArchitectureInfo {
-                    name: "WASM".to_string(),
-                    description: "WebAssembly virtual machine".to_string(),
-                    features: vec!["basic_execution".to_string()],
-                    is_default: true,
-                    performance: None,
-                },
-                proto::vm_service::ArchitectureInfo {
-                    name: "X86_64".to_string(),
-                    description: "Native x86-64 execution".to_string(),
-                    features: vec!["native_execution".to_string()],
-                    is_default: false,
-                    performance: None,
-                },
-            ],
+            architectures: architecture_infos(self.dots.architectures()),
         };
         Ok(Response::new(response))
     }
 
-    async fn get_vm_status(&self, _request: Request<proto::vm_service::GetVmStatusRequest>) -> Result<Response<proto::vm_service::GetVmStatusResponse>, Status> {
+    async fn get_vm_status(&self, request: Request<proto::vm_service::GetVmStatusRequest>) -> Result<Response<proto::vm_service::GetVmStatusResponse>, Status> {
         println!("GetVMStatus called");
+        let paradots = self.dots.paradots();
         let response = proto::vm_service::GetVmStatusResponse {
             status: 1, // Running
             active_dots: vec![],
             info: Some(proto::vm_service::VmInfo {
-                architecture: "WASM".to_string(),
+                architecture: self.dots.architectures().default.to_string(),
                 uptime_seconds: 3600,
                 version: "0.1.0".to_string(),
                 dots_count: 0,
-                paradots_count: 0,
+                paradots_count: paradots.len() as u32,
                 resource_usage: Some(proto::vm_service::ResourceUsage {
                     memory_used_bytes: 1024 * 1024,
                     memory_total_bytes: 8 * 1024 * 1024,
@@ -112,22 +142,27 @@ impl VmService for VmServiceImpl {
                     active_connections: 1,
                 }),
             }),
-            active_paradots: vec![],
+            active_paradots: paradots.iter().map(|paradot| paradot.paradot_id.clone()).collect(),
+            paradots: if request.get_ref().include_details { paradots } else { vec![] },
         };
         Ok(Response::new(response))
     }
 
-    async fn get_vm_metrics(&self, _request: Request<proto::vm_service::GetVmMetricsRequest>) -> Result<Response<proto::vm_service::GetVmMetricsResponse>, Status> {
+    async fn get_vm_metrics(&self, request: Request<proto::vm_service::GetVmMetricsRequest>) -> Result<Response<proto::vm_service::GetVmMetricsResponse>, Status> {
         println!("GetVMMetrics called");
-        let response = proto::vm_service::GetVmMetricsResponse {
-            metrics: vec![proto::vm_service::VmMetric {
-                name: "cpu_usage".to_string(),
-                r#type: "gauge".to_string(),
-                data_points: vec![],
-                labels: std::collections::HashMap::new(),
-            }],
-        };
-        Ok(Response::new(response))
+        self.metrics.get_vm_metrics(request).await
+    }
+
+    async fn set_memory_tracking(&self, request: Request<proto::vm_service::SetMemoryTrackingRequest>) -> Result<Response<proto::vm_service::SetMemoryTrackingResponse>, Status> {
+        self.metrics.set_memory_tracking(request).await
+    }
+
+    async fn query_logs(&self, request: Request<proto::vm_service::QueryLogsRequest>) -> Result<Response<proto::vm_service::QueryLogsResponse>, Status> {
+        self.logs.query_logs(request).await
+    }
+
+    async fn ingest_logs(&self, request: Request<proto::vm_service::IngestLogsRequest>) -> Result<Response<proto::vm_service::IngestLogsResponse>, Status> {
+        self.logs.ingest_logs(request).await
     }
 
     // VM Service Ping - working implementation
@@ -200,74 +235,89 @@ impl VmService for VmServiceImpl {
         Ok(Response::new(response))
     }
 
-    // Basic implementations to avoid RST_STREAM errors
     async fn execute_dot(&self, request: Request<proto::vm_service::ExecuteDotRequest>) -> Result<Response<proto::vm_service::ExecuteDotResponse>, Status> {
-        let req = request.into_inner();
-        println!("ExecuteDot called for dot_id: {}", req.dot_id);
-
-        let response = proto::vm_service::ExecuteDotResponse {
-            success: false,
-            outputs: std::collections::HashMap::new(),
-            execution_time_ms: 0,
-            paradots_used: vec![],
-            logs: vec![],
-            events: vec![],
-            error_message: "ExecuteDot not yet implemented - this is a placeholder response".to_string(),
-            metrics: None,
-        };
-        Ok(Response::new(response))
+        self.dots.execute_dot(request).await
     }
 
     async fn deploy_dot(&self, request: Request<proto::vm_service::DeployDotRequest>) -> Result<Response<proto::vm_service::DeployDotResponse>, Status> {
-        let req = request.into_inner();
-        println!("DeployDot called for dot_name: {}", req.dot_name);
+        self.dots.deploy_dot(request).await
+    }
 
-        let response = proto::vm_service::DeployDotResponse {
-            success: false,
-            dot_id: "".to_string(),
-            bytecode: vec![],
-            abi: None,
-            metrics: None,
-            error_message: "DeployDot not yet implemented - this is a placeholder response".to_string(),
-        };
-        Ok(Response::new(response))
+    async fn deploy_dot_stream(&self, request: Request<tonic::Streaming<proto::vm_service::DeployDotChunk>>) -> Result<Response<proto::vm_service::DeployDotResponse>, Status> {
+        self.dots.deploy_dot_stream(request).await
     }
 
-    async fn get_dot_state(&self, request: Request<proto::vm_service::GetDotStateRequest>) -> Result<Response<proto::vm_service::GetDotStateResponse>, Status> {
-        let req = request.into_inner();
-        println!("GetDotState called for dot_id: {}", req.dot_id);
+    type DeployDotWithProgressStream = std::pin::Pin<Box<dyn futures::Stream<Item = Result<proto::vm_service::DeployDotProgress, Status>> + Send>>;
 
-        let response = proto::vm_service::GetDotStateResponse {
-            success: false,
-            state_data: std::collections::HashMap::new(),
-            state_root_hash: "".to_string(),
-            version: 0,
-            error_message: "GetDotState not yet implemented - this is a placeholder response".to_string(),
-        };
-        Ok(Response::new(response))
+    async fn deploy_dot_with_progress(&self, request: Request<proto::vm_service::DeployDotRequest>) -> Result<Response<Self::DeployDotWithProgressStream>, Status> {
+        let response = self.dots.deploy_dot_with_progress(request)?;
+        Ok(response.map(|stream| Box::pin(stream) as Self::DeployDotWithProgressStream))
     }
 
-    async fn list_dots(&self, _request: Request<proto::vm_service::ListDotsRequest>) -> Result<Response<proto::vm_service::ListDotsResponse>, Status> {
-        println!("ListDots called");
+    async fn get_dot_state(&self, request: Request<proto::vm_service::GetDotStateRequest>) -> Result<Response<proto::vm_service::GetDotStateResponse>, Status> {
+        self.dots.get_dot_state(request).await
+    }
 
-        let response = proto::vm_service::ListDotsResponse {
-            dots: vec![], // Empty list for now
-            total_count: 0,
-            next_cursor: "".to_string(),
-            has_more: false,
-        };
-        Ok(Response::new(response))
+    async fn get_state_diff(&self, request: Request<proto::vm_service::GetStateDiffRequest>) -> Result<Response<proto::vm_service::GetStateDiffResponse>, Status> {
+        self.dots.get_state_diff(request).await
+    }
+
+    async fn list_dots(&self, request: Request<proto::vm_service::ListDotsRequest>) -> Result<Response<proto::vm_service::ListDotsResponse>, Status> {
+        self.dots.list_dots(request).await
     }
 
     async fn delete_dot(&self, request: Request<proto::vm_service::DeleteDotRequest>) -> Result<Response<proto::vm_service::DeleteDotResponse>, Status> {
-        let req = request.into_inner();
-        println!("DeleteDot called for dot_id: {}", req.dot_id);
+        let dot_id = request.get_ref().dot_id.clone();
+        let response = self.dots.delete_dot(request).await?;
+        // The ABI history outlives the dot so its past executions stay interpretable
+        if response.get_ref().success {
+            self.abi.archive_dot(&dot_id).await;
+        }
+        Ok(response)
+    }
 
-        let response = proto::vm_service::DeleteDotResponse {
-            success: false,
-            error_message: "DeleteDot not yet implemented - this is a placeholder response".to_string(),
-        };
-        Ok(Response::new(response))
+    async fn activate_dot_version(&self, request: Request<proto::vm_service::ActivateDotVersionRequest>) -> Result<Response<proto::vm_service::ActivateDotVersionResponse>, Status> {
+        self.dots.activate_dot_version(request).await
+    }
+
+    async fn set_dot_quota(&self, request: Request<proto::vm_service::SetDotQuotaRequest>) -> Result<Response<proto::vm_service::SetDotQuotaResponse>, Status> {
+        self.dots.set_dot_quota(request).await
+    }
+
+    async fn get_dot_quota(&self, request: Request<proto::vm_service::GetDotQuotaRequest>) -> Result<Response<proto::vm_service::GetDotQuotaResponse>, Status> {
+        self.dots.get_dot_quota(request).await
+    }
+
+    async fn set_dot_caching(&self, request: Request<proto::vm_service::SetDotCachingRequest>) -> Result<Response<proto::vm_service::SetDotCachingResponse>, Status> {
+        self.dots.set_dot_caching(request).await
+    }
+
+    async fn get_dot_cache_stats(&self, request: Request<proto::vm_service::GetDotCacheStatsRequest>) -> Result<Response<proto::vm_service::GetDotCacheStatsResponse>, Status> {
+        self.dots.get_dot_cache_stats(request).await
+    }
+
+    async fn list_checkpoints(&self, request: Request<proto::vm_service::ListCheckpointsRequest>) -> Result<Response<proto::vm_service::ListCheckpointsResponse>, Status> {
+        self.dots.list_checkpoints(request).await
+    }
+
+    async fn restore_checkpoint(&self, request: Request<proto::vm_service::RestoreCheckpointRequest>) -> Result<Response<proto::vm_service::RestoreCheckpointResponse>, Status> {
+        self.dots.restore_checkpoint(request).await
+    }
+
+    async fn spawn_para_dot(&self, request: Request<proto::vm_service::SpawnParaDotRequest>) -> Result<Response<proto::vm_service::SpawnParaDotResponse>, Status> {
+        self.dots.spawn_paradot(request).await
+    }
+
+    async fn list_para_dots(&self, request: Request<proto::vm_service::ListParaDotsRequest>) -> Result<Response<proto::vm_service::ListParaDotsResponse>, Status> {
+        self.dots.list_paradots(request).await
+    }
+
+    async fn execute_para_dot(&self, request: Request<proto::vm_service::ExecuteParaDotRequest>) -> Result<Response<proto::vm_service::ExecuteParaDotResponse>, Status> {
+        self.dots.execute_paradot(request).await
+    }
+
+    async fn terminate_para_dot(&self, request: Request<proto::vm_service::TerminateParaDotRequest>) -> Result<Response<proto::vm_service::TerminateParaDotResponse>, Status> {
+        self.dots.terminate_paradot(request).await
     }
 
     async fn get_bytecode(&self, request: Request<proto::vm_service::GetBytecodeRequest>) -> Result<Response<proto::vm_service::GetBytecodeResponse>, Status> {
@@ -300,15 +350,7 @@ impl VmService for VmServiceImpl {
     }
 
     async fn get_dot_abi(&self, request: Request<proto::vm_service::GetDotAbiRequest>) -> Result<Response<proto::vm_service::GetDotAbiResponse>, Status> {
-        let req = request.into_inner();
-        println!("GetDotABI called for dot_id: {}", req.dot_id);
-
-        let response = proto::vm_service::GetDotAbiResponse {
-            success: false,
-            abi: None,
-            error_message: "GetDotABI not yet implemented - this is a placeholder response".to_string(),
-        };
-        Ok(Response::new(response))
+        self.abi.get_dot_abi(request).await
     }
 
     async fn validate_abi(&self, request: Request<proto::vm_service::ValidateAbiRequest>) -> Result<Response<proto::vm_service::ValidateAbiResponse>, Status> {
@@ -328,73 +370,59 @@ impl VmService for VmServiceImpl {
     }
 
     async fn generate_abi(&self, request: Request<proto::vm_service::GenerateAbiRequest>) -> Result<Response<proto::vm_service::GenerateAbiResponse>, Status> {
-        let req = request.into_inner();
         println!("GenerateABI called");
-
-        let response = proto::vm_service::GenerateAbiResponse {
-            success: false,
-            abi: None,
-            warnings: vec![],
-            error_message: "GenerateABI not yet implemented - this is a placeholder response".to_string(),
-        };
-        Ok(Response::new(response))
+        self.abi.generate_abi(request).await
     }
 
     async fn register_abi(&self, request: Request<proto::vm_service::RegisterAbiRequest>) -> Result<Response<proto::vm_service::RegisterAbiResponse>, Status> {
-        let req = request.into_inner();
-        println!("RegisterABI called for dot_id: {}", req.dot_id);
+        self.abi.register_abi(request).await
+    }
 
-        let response = proto::vm_service::RegisterAbiResponse {
-            success: false,
-            abi_version: "0".to_string(),
-            error_message: "RegisterABI not yet implemented - this is a placeholder response".to_string(),
-        };
-        Ok(Response::new(response))
+    async fn list_abi_versions(&self, request: Request<proto::vm_service::ListAbiVersionsRequest>) -> Result<Response<proto::vm_service::ListAbiVersionsResponse>, Status> {
+        self.abi.list_abi_versions(request).await
     }
 
     type StreamDotEventsStream = std::pin::Pin<Box<dyn futures::Stream<Item = Result<proto::vm_service::DotEvent, Status>> + Send>>;
 
-    async fn stream_dot_events(&self, _request: Request<proto::vm_service::StreamDotEventsRequest>) -> Result<Response<Self::StreamDotEventsStream>, Status> {
-        println!("StreamDotEvents called - returning empty stream");
+    async fn stream_dot_events(&self, request: Request<proto::vm_service::StreamDotEventsRequest>) -> Result<Response<Self::StreamDotEventsStream>, Status> {
+        let req = request.into_inner();
+        let from_sequence = dot_events::resume_sequence(&req);
+        println!("StreamDotEvents called - from sequence {}", from_sequence);
 
-        // Create an empty stream that completes immediately
-        let stream = futures::stream::empty();
+        let filter = dot_events::create_filter_from_request(&req);
+        let stream = self.events.subscribe(uuid::Uuid::new_v4().to_string(), filter, from_sequence).await;
         Ok(Response::new(Box::pin(stream)))
     }
 
     type StreamVMMetricsStream = std::pin::Pin<Box<dyn futures::Stream<Item = Result<proto::vm_service::VmMetric, Status>> + Send>>;
 
-    async fn stream_vm_metrics(&self, _request: Request<proto::vm_service::StreamVmMetricsRequest>) -> Result<Response<Self::StreamVMMetricsStream>, Status> {
-        println!("StreamVMMetrics called - returning empty stream");
-
-        // Create an empty stream that completes immediately
-        let stream = futures::stream::empty();
-        Ok(Response::new(Box::pin(stream)))
+    async fn stream_vm_metrics(&self, request: Request<proto::vm_service::StreamVmMetricsRequest>) -> Result<Response<Self::StreamVMMetricsStream>, Status> {
+        let response = self.metrics.stream_vm_metrics(request).await?;
+        Ok(response.map(|stream| Box::pin(stream) as Self::StreamVMMetricsStream))
     }
 
     type InteractiveDotExecutionStream = std::pin::Pin<Box<dyn futures::Stream<Item = Result<proto::vm_service::InteractiveExecutionResponse, Status>> + Send>>;
 
-    async fn interactive_dot_execution(&self, _request: Request<tonic::Streaming<proto::vm_service::InteractiveExecutionRequest>>) -> Result<Response<Self::InteractiveDotExecutionStream>, Status> {
-        println!("InteractiveDotExecution called - returning empty stream");
-
-        // Create an empty stream that completes immediately
-        let stream = futures::stream::empty();
-        Ok(Response::new(Box::pin(stream)))
+    async fn interactive_dot_execution(&self, request: Request<tonic::Streaming<proto::vm_service::InteractiveExecutionRequest>>) -> Result<Response<Self::InteractiveDotExecutionStream>, Status> {
+        let response = self.dots.interactive_dot_execution(request).await?;
+        Ok(response.map(|stream| Box::pin(stream) as Self::InteractiveDotExecutionStream))
     }
 
     type LiveDotDebuggingStream = std::pin::Pin<Box<dyn futures::Stream<Item = Result<proto::vm_service::DebugResponse, Status>> + Send>>;
 
-    async fn live_dot_debugging(&self, _request: Request<tonic::Streaming<proto::vm_service::DebugRequest>>) -> Result<Response<Self::LiveDotDebuggingStream>, Status> {
-        println!("LiveDotDebugging called - returning empty stream");
-
-        // Create an empty stream that completes immediately
-        let stream = futures::stream::empty();
-        Ok(Response::new(Box::pin(stream)))
+    async fn live_dot_debugging(&self, request: Request<tonic::Streaming<proto::vm_service::DebugRequest>>) -> Result<Response<Self::LiveDotDebuggingStream>, Status> {
+        let response = self.dots.live_dot_debugging(request).await?;
+        Ok(response.map(|stream| Box::pin(stream) as Self::LiveDotDebuggingStream))
     }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Logs always, spans exported over OTLP when OTEL_EXPORTER_OTLP_ENDPOINT is set. Events are
+    // also captured for the centralized log store, buffered until the store is open.
+    let (log_capture, log_events) = dotvm_common::log_capture::log_capture("dotvm-runtime", 10_000);
+    let _tracing = dotvm_common::telemetry::init_tracing_with_capture("dotvm-runtime", Some(log_capture))?;
+
     // Set up graceful shutdown
     let (shutdown_tx, mut shutdown_rx) = tokio::sync::mpsc::channel::<()>(1);
 
@@ -411,24 +439,168 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let runtime_config = RuntimeConfig::from_env();
     let addr = runtime_config.get_bind_address_for_platform();
     let runtime_service = SimpleRuntimeService::default();
-    let vm_service = VmServiceImpl::default();
+    // Redeploys and storage config changes share one set of checkpoints, listed and restored through the VM service
+    let checkpoints = Arc::new(OperationCheckpoints::with_retention(runtime_config.checkpoints_per_category));
     let cluster_service = ClusterServiceImpl::default();
-    let database_service = DatabaseServiceImpl::default();
+    let mut database_service = DatabaseServiceImpl::default().with_checkpoints(checkpoints);
+    let storage = match &runtime_config.storage_path {
+        Some(path) => {
+            let storage = Arc::new(StorageEngine::open(StorageConfig {
+                path: path.clone(),
+                ..StorageConfig::default()
+            })?);
+            println!("Storage engine opened at {}", path.display());
+            database_service = database_service.with_storage(Arc::clone(&storage));
+            Some(storage)
+        }
+        None => None,
+    };
+
+    // Every node replicates its documents: replicas follow their primary, other nodes serve their change feed
+    let documents = match (&runtime_config.document_path, runtime_config.document_memory_budget) {
+        (Some(path), _) => {
+            let db = Arc::new(Database::new(path, DbConfig::default())?);
+            // Document segments are compacted in the background, backing off while the storage engine's page writes are slow
+            if let (Some(storage), Some(compaction)) = (&storage, db.compaction()) {
+                storage.attach_compaction(compaction);
+            }
+            // Writes are counted towards the statistics kept beside the documents, which re-analyze collections as they change
+            let statistics = Arc::new(
+                StatisticsCollector::open(StatisticsConfig {
+                    persistence_path: Some(path.join(STATISTICS_FILE)),
+                    ..StatisticsConfig::default()
+                })
+                .await?,
+            );
+            let (documents, _recording) = CollectionManager::new(Arc::new(DocumentStore::open(db)?)).with_statistics(&statistics);
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(60));
+                loop {
+                    interval.tick().await;
+                    if let Err(e) = statistics.persist().await {
+                        tracing::warn!("Failed to persist statistics: {}", e);
+                    }
+                }
+            });
+            documents
+        }
+        (None, None) => create_in_memory_collection_manager()?,
+        (None, Some(budget)) => create_in_memory_collection_manager_with_budget(budget)?,
+    };
+    // Dot state is kept beside the documents too, so it survives restarts with them
+    let dot_state = match &runtime_config.document_path {
+        Some(path) => {
+            let db = Arc::new(Database::new(path.join(DOT_STATE_DIR), DbConfig::default())?);
+            Arc::new(DotStateStore::open(db, DEFAULT_MAX_VERSIONS_PER_DOT)?)
+        }
+        None => Arc::new(DotStateStore::default()),
+    };
+    // Registered ABIs live beside the documents, so they persist and replicate with them
+    let abi = Arc::new(AbiService::with_documents(documents.with_namespace(ABI_NAMESPACE)?));
+    // Execution traces are kept there as well, where `dotvm replay` finds them
+    let traces = Arc::new(TraceStore::open(documents.with_namespace(ABI_NAMESPACE)?)?);
+    // Executions record their latencies into the histograms GetVMMetrics and StreamVMMetrics report
+    let latencies = Arc::new(LatencyHistograms::new(runtime_config.latency.clone())?);
+    let mut vm_service = VmServiceImpl {
+        dots: Arc::new(
+            DotsService::new()
+                .with_drain_grace_period(Duration::from_secs(runtime_config.dot_drain_grace_period_secs))
+                .with_result_cache_capacity(runtime_config.dot_result_cache_bytes)
+                .with_state_store(dot_state)
+                .with_recording(Arc::new(ExecutionRecorder::new(traces, runtime_config.recording.clone())))
+                .with_latencies(latencies.clone())
+                .with_checkpoints(checkpoints.clone())
+                .with_max_artifact_size(runtime_config.max_artifact_bytes)
+                .with_architectures(runtime_config.architectures.clone())
+                // Executions are checked against the permissions of the ABIs registered there
+                .with_permissions(PermissionEvaluator::new(abi.registry())),
+        ),
+        abi,
+        metrics: Arc::new(MetricsService::new().with_latencies(latencies)),
+        ..Default::default()
+    };
+    // Emitted dot events are checked against the event schemas of the registered ABIs
+    vm_service.events = Arc::new(DotEventBroadcaster::new().with_schemas(vm_service.abi.registry(), runtime_config.event_schema_strictness));
+    // Centralized logs are documents too, recorded under the node's replica id or bind address
+    let log_node = runtime_config.replica_id.clone().unwrap_or_else(|| addr.to_string());
+    let log_store = LogStore::open(documents.with_namespace(ABI_NAMESPACE)?, &log_node, runtime_config.log_retention)?;
+    vm_service.logs = Arc::new(LogsService::with_store(Arc::new(log_store)));
+    vm_service.logs.spawn_shipper(log_events)?;
+    vm_service.logs.spawn_retention(Duration::from_secs(60));
+    let replication = match &runtime_config.replica_of {
+        Some(primary) => {
+            let replica = Arc::new(Replica::new(documents.storage().clone(), primary.clone()));
+            let replica_id = runtime_config.replica_id.clone().unwrap_or_else(|| addr.to_string());
+            let mut follower = ReplicaFollower::new(replica, replica_id, addr.to_string());
+            if let Some(key) = &runtime_config.replication_api_key {
+                follower = follower.with_api_key(key);
+            }
+            let follower = Arc::new(follower);
+            follower.spawn();
+            println!("Following primary {} as a read-only replica", primary);
+            ReplicationRole::Replica(follower)
+        }
+        None => {
+            let feed = Arc::new(ChangeFeed::new(runtime_config.change_feed_retention));
+            ReplicationRole::Primary(Arc::new(ReplicationPrimary::new(Arc::new(ChangeFeedStorage::new(documents.storage().clone(), feed)))))
+        }
+    };
+    database_service = database_service.with_replication(replication.clone());
 
     // Start background tasks for cluster service
     cluster_service.start_background_tasks().await;
 
-    // Set up reflection service
+    let tls = match runtime_config.tls.clone() {
+        Some(tls_config) => {
+            let tls = Arc::new(ReloadableTls::load(tls_config)?);
+            tls.spawn_reloader();
+            Some(tls)
+        }
+        None => None,
+    };
+    let mutual_tls = tls.as_ref().is_some_and(|tls| tls.config().is_mutual());
+
+    // Set up reflection service; with mutual TLS only clients with a certificate may use it
     let reflection_service = tonic_reflection::server::Builder::configure()
         .register_encoded_file_descriptor_set(proto::FILE_DESCRIPTOR_SET)
         .build()?;
+    let reflection_service = InterceptedService::new(reflection_service, move |request| if mutual_tls { tls::require_client_cert(request) } else { Ok(request) });
+
+    // Every call is checked against the method's required access level once a key file is configured
+    let auth_layer = match &runtime_config.auth {
+        Some(auth_config) => {
+            let store = StaticKeyStore::load(&auth_config.keys_path)?;
+            println!("API keys required: {} key(s) loaded from {}", store.len(), auth_config.keys_path.display());
+            let policy = MethodPolicy::new(auth_config.public_health_check, auth_config.public_reflection);
+            AuthLayer::new(Arc::new(Authenticator::new(Arc::new(store), policy)))
+        }
+        None => {
+            println!("Warning: DOTVM_AUTH_KEYS_FILE is not set, every caller may use every method");
+            AuthLayer::disabled()
+        }
+    };
 
     println!("Server starting on {}", addr);
     println!("Basic functionality ready");
     println!("VM service enabled");
     println!("Cluster service enabled (CONN-002 features)");
     println!("Database service enabled");
-    println!("gRPC reflection enabled");
+    println!("gRPC reflection enabled{}", if mutual_tls { " (client certificate required)" } else { "" });
+    if let Some(tls) = &tls {
+        let tls_config = tls.config();
+        println!("TLS enabled with {} (reloaded on SIGHUP or file change)", tls_config.cert_path.display());
+        if let Some(ca) = &tls_config.client_ca_path {
+            println!(
+                "Mutual TLS: client CA {}, client certificate {}",
+                ca.display(),
+                if tls_config.require_client_auth { "required" } else { "optional" }
+            );
+        }
+        println!("The grpcurl examples below use -plaintext; replace it with -cacert/-cert/-key");
+    }
+    if runtime_config.auth.is_some() {
+        println!("The grpcurl examples below need -H 'authorization: Bearer <key>'");
+    }
     println!("");
     println!("Test with:");
     println!("  grpcurl -plaintext -d '{{\"message\": \"hello\"}}' {} runtime.Runtime/Ping", addr);
@@ -445,6 +617,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Database Service:");
     println!("  grpcurl -plaintext -d '{{\"include_details\": true}}' {} database_service.DatabaseService/GetDatabaseStatus", addr);
     println!("  grpcurl -plaintext -d '{{\"pattern\": \"\"}}' {} database_service.DatabaseService/ListCollections", addr);
+    println!("  grpcurl -plaintext -d '{{}}' {} database_service.DatabaseService/GetReplicationStatus", addr);
     println!("");
     println!("Cross-platform connection tips:");
     println!("  Ubuntu/Linux: Use 127.0.0.1:{} (recommended) or localhost:{}", addr.port(), addr.port());
@@ -457,17 +630,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Starting server with graceful shutdown support...");
     println!("Press Ctrl+C to stop the server and free the port");
 
-    Server::builder()
+    let router = Server::builder()
+        .layer(auth_layer)
         .add_service(reflection_service)
         .add_service(RuntimeServer::new(runtime_service))
         .add_service(VmServiceServer::new(vm_service))
         .add_service(ClusterServiceServer::new(cluster_service))
-        .add_service(DatabaseServiceServer::new(database_service))
-        .serve_with_shutdown(addr, async {
-            shutdown_rx.recv().await;
-            println!("Shutdown signal received, stopping server...");
-        })
-        .await?;
+        .add_service(DatabaseServiceServer::new(database_service));
+    let shutdown = async move {
+        shutdown_rx.recv().await;
+        println!("Shutdown signal received, stopping server...");
+        replication.close_streams();
+    };
+
+    match tls {
+        Some(tls) => {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            router.serve_with_incoming_shutdown(tls::incoming(listener, tls), shutdown).await?;
+        }
+        None => router.serve_with_shutdown(addr, shutdown).await?,
+    }
 
     println!("Server stopped, port 50051 is now free");
 
@@ -96,6 +96,8 @@ pub enum Permission {
     // Administrative operations
     ManageUsers,
     ManageRoles,
+    ManageQuotas,
+    ManageMemoryTracking,
     ViewLogs,
     SystemAdmin,
     
@@ -108,9 +110,9 @@ impl Permission {
     pub fn allows_method(&self, method: &str) -> bool {
         match self {
             Permission::ExecuteDot => method.contains("ExecuteDot"),
-            Permission::DeployDot => method.contains("DeployDot"),
+            Permission::DeployDot => method.contains("DeployDot") || method.contains("ActivateDotVersion"),
             Permission::DeleteDot => method.contains("DeleteDot"),
-            Permission::GetDotState => method.contains("GetDotState"),
+            Permission::GetDotState => method.contains("GetDotState") || method.contains("GetStateDiff"),
             Permission::ListDots => method.contains("ListDots"),
             Permission::GetBytecode => method.contains("GetBytecode"),
             Permission::ValidateBytecode => method.contains("ValidateBytecode"),
@@ -125,6 +127,8 @@ impl Permission {
             Permission::StreamVMMetrics => method.contains("StreamVMMetrics"),
             Permission::ManageUsers => method.contains("User"),
             Permission::ManageRoles => method.contains("Role"),
+            Permission::ManageQuotas => method.contains("DotQuota"),
+            Permission::ManageMemoryTracking => method.contains("MemoryTracking"),
             Permission::ViewLogs => method.contains("Log"),
             Permission::SystemAdmin => true, // Admin can access everything
             Permission::Custom(perm) => method.contains(perm),
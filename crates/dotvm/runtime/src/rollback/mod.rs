@@ -1,10 +1,12 @@
 pub mod checkpoint;
 pub mod lib;
+pub mod operation;
 pub mod recovery;
 pub mod state;
 pub mod verification;
 
 pub use checkpoint::{Checkpoint, CheckpointManager};
+pub use operation::{CheckpointCategory, CheckpointStatus, CheckpointTarget, OperationCheckpoint, OperationCheckpoints};
 pub use recovery::{RecoveryManager, RecoveryResult};
 pub use state::{RollbackManager, RollbackTrigger, StateRollback};
 pub use verification::{ConsistencyVerifier, VerificationResult};
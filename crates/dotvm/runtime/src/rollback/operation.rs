@@ -0,0 +1,371 @@
+use crate::rollback::checkpoint::Checkpoint;
+use crate::rollback::lib::{LogLevel, RollbackError, RollbackResult, SystemState, generate_checkpoint_id, log_event};
+use crate::rollback::verification::{ConsistencyVerifier, VerificationResult};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Checkpoints kept per category before the oldest ones are dropped
+pub const DEFAULT_CHECKPOINTS_PER_CATEGORY: usize = 10;
+
+/// Kind of risky operation a checkpoint was taken for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CheckpointCategory {
+    /// A new version deployed under an existing dot
+    DotRedeploy,
+    /// Collection schema or index changes in storage
+    StorageSchema,
+    /// Configuration changes applied to a running component
+    ConfigMigration,
+}
+
+impl CheckpointCategory {
+    pub const ALL: [CheckpointCategory; 3] = [CheckpointCategory::DotRedeploy, CheckpointCategory::StorageSchema, CheckpointCategory::ConfigMigration];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CheckpointCategory::DotRedeploy => "dot_redeploy",
+            CheckpointCategory::StorageSchema => "storage_schema",
+            CheckpointCategory::ConfigMigration => "config_migration",
+        }
+    }
+
+    /// Parses a category name as printed by [`CheckpointCategory::as_str`]
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|category| category.as_str() == name.trim().replace('-', "_"))
+    }
+}
+
+impl fmt::Display for CheckpointCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What happened to the operation a checkpoint guards
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckpointStatus {
+    /// The operation has not been verified yet
+    Pending,
+    /// The operation passed verification
+    Verified,
+    /// Verification failed and the checkpoint was restored automatically
+    RolledBack,
+    /// The checkpoint was restored on request
+    Restored,
+}
+
+impl CheckpointStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CheckpointStatus::Pending => "pending",
+            CheckpointStatus::Verified => "verified",
+            CheckpointStatus::RolledBack => "rolled_back",
+            CheckpointStatus::Restored => "restored",
+        }
+    }
+}
+
+/// State captured before an operation, recorded under the operation's id
+#[derive(Debug, Clone)]
+pub struct OperationCheckpoint {
+    pub checkpoint: Checkpoint,
+    pub operation_id: String,
+    pub category: CheckpointCategory,
+    /// What the operation changes, such as a dot ID
+    pub subject: String,
+    pub status: CheckpointStatus,
+    /// Why verification failed, if it did
+    pub failure: Option<String>,
+}
+
+/// Captures and restores the state one category of operations changes
+pub trait CheckpointTarget: Send + Sync {
+    /// Captures the current state of `subject`
+    fn capture(&self, subject: &str) -> RollbackResult<SystemState>;
+
+    /// Puts `subject` back to a previously captured state
+    fn restore(&self, subject: &str, state: &SystemState) -> RollbackResult<()>;
+}
+
+/// Checkpoints taken around risky operations, restorable until they are retired
+///
+/// A checkpoint is captured through the target registered for its category
+/// before the operation runs. Afterwards [`OperationCheckpoints::verify`]
+/// checks the resulting state and puts the checkpoint back when a check fails.
+/// Only the newest checkpoints of each category are kept.
+pub struct OperationCheckpoints {
+    /// Oldest first
+    checkpoints: Mutex<Vec<OperationCheckpoint>>,
+    targets: RwLock<HashMap<CheckpointCategory, Arc<dyn CheckpointTarget>>>,
+    keep_per_category: usize,
+    next_sequence: AtomicU64,
+}
+
+impl OperationCheckpoints {
+    pub fn new() -> Self {
+        Self::with_retention(DEFAULT_CHECKPOINTS_PER_CATEGORY)
+    }
+
+    /// Keeps the newest `keep_per_category` checkpoints of each category, at least one
+    pub fn with_retention(keep_per_category: usize) -> Self {
+        Self {
+            checkpoints: Mutex::new(Vec::new()),
+            targets: RwLock::new(HashMap::new()),
+            keep_per_category: keep_per_category.max(1),
+            next_sequence: AtomicU64::new(0),
+        }
+    }
+
+    /// Captures and restores checkpoints of `category` through `target`, replacing any earlier target
+    pub fn register_target(&self, category: CheckpointCategory, target: Arc<dyn CheckpointTarget>) {
+        self.targets.write().unwrap().insert(category, target);
+    }
+
+    /// Captures the state of `subject` before `operation_id` changes it, returning the checkpoint ID
+    pub fn create(&self, category: CheckpointCategory, operation_id: &str, subject: &str) -> RollbackResult<String> {
+        let state = self.target(category)?.capture(subject)?;
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        // Checkpoints taken within the same clock tick still get distinct IDs
+        let id = format!("{}-{}", generate_checkpoint_id(), self.next_sequence.fetch_add(1, Ordering::Relaxed));
+        let checkpoint = OperationCheckpoint {
+            checkpoint: Checkpoint {
+                id: id.clone(),
+                timestamp: now.as_secs() * 1_000_000_000 + now.subsec_nanos() as u64,
+                state,
+            },
+            operation_id: operation_id.to_string(),
+            category,
+            subject: subject.to_string(),
+            status: CheckpointStatus::Pending,
+            failure: None,
+        };
+
+        let mut checkpoints = self.lock()?;
+        checkpoints.push(checkpoint);
+        let kept = checkpoints.iter().filter(|c| c.category == category).count();
+        if kept > self.keep_per_category {
+            let mut excess = kept - self.keep_per_category;
+            checkpoints.retain(|c| {
+                let retire = excess > 0 && c.category == category;
+                if retire {
+                    excess -= 1;
+                    log_event(LogLevel::Info, "OperationCheckpoints", &format!("Retired {} checkpoint: {}", category, c.checkpoint.id));
+                }
+                !retire
+            });
+        }
+        log_event(
+            LogLevel::Info,
+            "OperationCheckpoints",
+            &format!("Created {category} checkpoint {id} of {subject} for operation {operation_id}"),
+        );
+
+        Ok(id)
+    }
+
+    /// Checks the state the operation left behind, restoring the checkpoint when a check fails
+    ///
+    /// Returns [`RollbackError::VerificationFailed`] once the checkpoint is back in
+    /// place, or [`RollbackError::RollbackFailed`] if it could not be restored.
+    pub fn verify(&self, checkpoint_id: &str, verifier: &impl ConsistencyVerifier) -> RollbackResult<()> {
+        let checkpoint = self.get(checkpoint_id)?;
+        let target = self.target(checkpoint.category)?;
+        let state = target.capture(&checkpoint.subject)?;
+
+        let reason = match verifier.verify_consistency(&state) {
+            VerificationResult::Valid => {
+                self.set_status(checkpoint_id, CheckpointStatus::Verified, None)?;
+                return Ok(());
+            }
+            VerificationResult::Invalid(reason) => reason,
+        };
+
+        log_event(
+            LogLevel::Warning,
+            "OperationCheckpoints",
+            &format!("Operation {} failed verification, restoring checkpoint {}: {}", checkpoint.operation_id, checkpoint_id, reason),
+        );
+        if let Err(e) = target.restore(&checkpoint.subject, &checkpoint.checkpoint.state) {
+            self.set_status(checkpoint_id, CheckpointStatus::Pending, Some(reason.clone()))?;
+            return Err(RollbackError::RollbackFailed(format!("{reason}; restoring checkpoint {checkpoint_id} failed: {e}")));
+        }
+        self.set_status(checkpoint_id, CheckpointStatus::RolledBack, Some(reason.clone()))?;
+
+        Err(RollbackError::VerificationFailed(reason))
+    }
+
+    /// Puts back the state a checkpoint captured
+    pub fn restore(&self, checkpoint_id: &str) -> RollbackResult<OperationCheckpoint> {
+        let checkpoint = self.get(checkpoint_id)?;
+        self.target(checkpoint.category)?.restore(&checkpoint.subject, &checkpoint.checkpoint.state)?;
+        log_event(LogLevel::Info, "OperationCheckpoints", &format!("Restored checkpoint {} of {}", checkpoint_id, checkpoint.subject));
+
+        self.set_status(checkpoint_id, CheckpointStatus::Restored, checkpoint.failure)?;
+        self.get(checkpoint_id)
+    }
+
+    pub fn get(&self, checkpoint_id: &str) -> RollbackResult<OperationCheckpoint> {
+        self.lock()?
+            .iter()
+            .find(|c| c.checkpoint.id == checkpoint_id)
+            .cloned()
+            .ok_or_else(|| RollbackError::CheckpointNotFound(checkpoint_id.to_string()))
+    }
+
+    /// Retained checkpoints newest first, of every category or only `category`
+    pub fn list(&self, category: Option<CheckpointCategory>) -> Vec<OperationCheckpoint> {
+        match self.checkpoints.lock() {
+            Ok(checkpoints) => checkpoints.iter().rev().filter(|c| category.is_none_or(|category| c.category == category)).cloned().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    fn target(&self, category: CheckpointCategory) -> RollbackResult<Arc<dyn CheckpointTarget>> {
+        self.targets
+            .read()
+            .unwrap()
+            .get(&category)
+            .cloned()
+            .ok_or_else(|| RollbackError::StateStorageError(format!("No checkpoint target registered for {category}")))
+    }
+
+    fn set_status(&self, checkpoint_id: &str, status: CheckpointStatus, failure: Option<String>) -> RollbackResult<()> {
+        // A checkpoint retired while its operation ran has nothing left to update
+        if let Some(checkpoint) = self.lock()?.iter_mut().find(|c| c.checkpoint.id == checkpoint_id) {
+            checkpoint.status = status;
+            checkpoint.failure = failure;
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> RollbackResult<std::sync::MutexGuard<'_, Vec<OperationCheckpoint>>> {
+        self.checkpoints.lock().map_err(|_| RollbackError::StateStorageError("Failed to acquire checkpoints lock".to_string()))
+    }
+}
+
+impl fmt::Debug for OperationCheckpoints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OperationCheckpoints").field("keep_per_category", &self.keep_per_category).finish_non_exhaustive()
+    }
+}
+
+impl Default for OperationCheckpoints {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rollback::verification::DefaultConsistencyVerifier;
+
+    /// A single value per subject, standing in for the state an operation changes
+    #[derive(Default)]
+    struct Values {
+        values: Mutex<HashMap<String, Vec<u8>>>,
+    }
+
+    impl Values {
+        fn set(&self, subject: &str, value: &[u8]) {
+            self.values.lock().unwrap().insert(subject.to_string(), value.to_vec());
+        }
+
+        fn value(&self, subject: &str) -> Vec<u8> {
+            self.values.lock().unwrap()[subject].clone()
+        }
+    }
+
+    impl CheckpointTarget for Values {
+        fn capture(&self, subject: &str) -> RollbackResult<SystemState> {
+            Ok(SystemState::from([("value".to_string(), self.value(subject))]))
+        }
+
+        fn restore(&self, subject: &str, state: &SystemState) -> RollbackResult<()> {
+            self.set(subject, &state["value"]);
+            Ok(())
+        }
+    }
+
+    fn checkpoints(keep: usize) -> (OperationCheckpoints, Arc<Values>) {
+        let values = Arc::new(Values::default());
+        let checkpoints = OperationCheckpoints::with_retention(keep);
+        for category in CheckpointCategory::ALL {
+            checkpoints.register_target(category, values.clone());
+        }
+        (checkpoints, values)
+    }
+
+    #[test]
+    fn test_failed_verification_restores_checkpoint() {
+        let (checkpoints, values) = checkpoints(5);
+        values.set("dot-1", b"v1");
+
+        let id = checkpoints.create(CheckpointCategory::DotRedeploy, "op-1", "dot-1").unwrap();
+        values.set("dot-1", b"broken");
+
+        let mut verifier = DefaultConsistencyVerifier::new();
+        verifier
+            .add_verification_check(
+                "not_broken",
+                |state| {
+                    if state["value"] == b"broken" {
+                        VerificationResult::Invalid("value is broken".to_string())
+                    } else {
+                        VerificationResult::Valid
+                    }
+                },
+                true,
+            )
+            .unwrap();
+
+        let error = checkpoints.verify(&id, &verifier).unwrap_err();
+        assert!(matches!(error, RollbackError::VerificationFailed(reason) if reason.contains("value is broken")));
+        assert_eq!(values.value("dot-1"), b"v1");
+
+        let checkpoint = checkpoints.get(&id).unwrap();
+        assert_eq!(checkpoint.status, CheckpointStatus::RolledBack);
+        assert_eq!(checkpoint.operation_id, "op-1");
+
+        // A passing operation keeps its changes
+        let id = checkpoints.create(CheckpointCategory::DotRedeploy, "op-2", "dot-1").unwrap();
+        values.set("dot-1", b"v2");
+        checkpoints.verify(&id, &verifier).unwrap();
+        assert_eq!(values.value("dot-1"), b"v2");
+        assert_eq!(checkpoints.get(&id).unwrap().status, CheckpointStatus::Verified);
+
+        // and can still be undone by hand
+        let restored = checkpoints.restore(&id).unwrap();
+        assert_eq!(restored.status, CheckpointStatus::Restored);
+        assert_eq!(values.value("dot-1"), b"v1");
+    }
+
+    #[test]
+    fn test_retention_is_per_category() {
+        let (checkpoints, values) = checkpoints(2);
+        values.set("dot-1", b"v1");
+        values.set("storage", b"config");
+
+        let redeploys: Vec<String> = (0..3).map(|i| checkpoints.create(CheckpointCategory::DotRedeploy, &format!("op-{i}"), "dot-1").unwrap()).collect();
+        let migration = checkpoints.create(CheckpointCategory::ConfigMigration, "op-3", "storage").unwrap();
+
+        assert!(matches!(checkpoints.get(&redeploys[0]), Err(RollbackError::CheckpointNotFound(_))));
+        let listed: Vec<String> = checkpoints.list(Some(CheckpointCategory::DotRedeploy)).into_iter().map(|c| c.checkpoint.id).collect();
+        assert_eq!(listed, vec![redeploys[2].clone(), redeploys[1].clone()]);
+        assert_eq!(checkpoints.list(None)[0].checkpoint.id, migration);
+        assert_eq!(checkpoints.list(None).len(), 3);
+    }
+
+    #[test]
+    fn test_category_names_round_trip() {
+        for category in CheckpointCategory::ALL {
+            assert_eq!(CheckpointCategory::parse(category.as_str()), Some(category));
+        }
+        assert_eq!(CheckpointCategory::parse("dot-redeploy"), Some(CheckpointCategory::DotRedeploy));
+        assert_eq!(CheckpointCategory::parse("schema"), None);
+    }
+}
@@ -0,0 +1,184 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Event schemas - checks the payloads of emitted dot events against their ABI
+//!
+//! An ABI declares each event type the dot emits with a version and the fields of its JSON
+//! payload. Emitted events are checked against the latest version of their type unless their
+//! [`EVENT_VERSION_METADATA`] names another. Dots whose ABI declares no events are not checked.
+
+use serde_json::Value;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use crate::proto::vm_service::{AbiEvent, AbiType};
+
+/// Metadata key of an emitted event naming the schema version its payload follows
+pub const EVENT_VERSION_METADATA: &str = "event_version";
+
+/// Metadata key under which a flagged event carries its schema mismatch
+pub const SCHEMA_VIOLATION_METADATA: &str = "schema_violation";
+
+/// How the runtime treats emitted events that do not match their declared schema
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EventSchemaStrictness {
+    /// Events are not checked
+    Off,
+    /// Mismatching events are delivered with a [`SCHEMA_VIOLATION_METADATA`] entry
+    #[default]
+    Flag,
+    /// Mismatching events are refused
+    Reject,
+}
+
+impl FromStr for EventSchemaStrictness {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "flag" => Ok(Self::Flag),
+            "reject" => Ok(Self::Reject),
+            _ => Err(format!("unknown event schema strictness '{}', expected off, flag or reject", value)),
+        }
+    }
+}
+
+/// Checks an emitted event against the events declared in its dot's ABI, describing the first mismatch
+pub fn check_event(declared: &[AbiEvent], event_type: &str, version: Option<u32>, payload: &[u8]) -> Result<(), String> {
+    if declared.is_empty() {
+        return Ok(());
+    }
+    let versions = declared.iter().filter(|event| event.name == event_type);
+    let schema = match version {
+        Some(version) => versions.into_iter().find(|event| event.version == version),
+        None => versions.max_by_key(|event| event.version),
+    };
+    let Some(schema) = schema else {
+        return Err(match version {
+            Some(version) => format!("version {} of event {} is not declared", version, event_type),
+            None => format!("event {} is not declared", event_type),
+        });
+    };
+
+    let Ok(Value::Object(payload)) = serde_json::from_slice::<Value>(payload) else {
+        return Err("payload is not a JSON object".to_string());
+    };
+    for field in &schema.fields {
+        match payload.get(&field.name) {
+            None | Some(Value::Null) if field.required => return Err(format!("required field {} is missing", field.name)),
+            None | Some(Value::Null) => {}
+            Some(value) if !matches_type(field.field_type.as_ref(), value) => {
+                let type_name = field.field_type.as_ref().map_or("none", |field_type| field_type.type_name.as_str());
+                return Err(format!("field {} is not a valid {}", field.name, type_name));
+            }
+            Some(_) => {}
+        }
+    }
+    let declared_fields: HashSet<&str> = schema.fields.iter().map(|field| field.name.as_str()).collect();
+    match payload.keys().find(|key| !declared_fields.contains(key.as_str())) {
+        Some(key) => Err(format!("field {} is not declared in version {} of event {}", key, schema.version, event_type)),
+        None => Ok(()),
+    }
+}
+
+/// Whether a JSON value is of an ABI type; the element type of an `Array<T>` is checked too
+fn matches_type(field_type: Option<&AbiType>, value: &Value) -> bool {
+    let Some(field_type) = field_type else {
+        return true;
+    };
+    match field_type.type_name.as_str() {
+        "String" | "DateTime" | "Binary" => value.is_string(),
+        "UUID" => value.as_str().is_some_and(|uuid| uuid::Uuid::parse_str(uuid).is_ok()),
+        "Integer" => value.is_i64() || value.is_u64(),
+        "Float" => value.is_number(),
+        "Currency" => value.is_number() || value.is_string(),
+        "Boolean" => value.is_boolean(),
+        "Object" => value.is_object(),
+        "Array" => value.as_array().is_some_and(|items| items.iter().all(|item| matches_type(field_type.generic_params.first(), item))),
+        // Unknown types are refused when the ABI is registered
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::vm_service::AbiField;
+
+    fn field(name: &str, type_name: &str, required: bool) -> AbiField {
+        AbiField {
+            name: name.to_string(),
+            field_type: Some(AbiType {
+                type_name: type_name.to_string(),
+                ..Default::default()
+            }),
+            required,
+            ..Default::default()
+        }
+    }
+
+    fn event(name: &str, version: u32, fields: Vec<AbiField>) -> AbiEvent {
+        AbiEvent {
+            name: name.to_string(),
+            version,
+            fields,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_payloads_are_checked_against_the_declared_version() {
+        let declared = vec![
+            event("transfer", 1, vec![field("to", "String", true), field("amount", "Integer", true)]),
+            event("transfer", 2, vec![field("to", "String", true), field("amount", "Float", true), field("memo", "String", false)]),
+        ];
+
+        assert_eq!(check_event(&declared, "transfer", None, br#"{"to": "bob", "amount": 1.5, "memo": null}"#), Ok(()));
+        assert_eq!(
+            check_event(&declared, "transfer", Some(1), br#"{"to": "bob", "amount": 1.5}"#),
+            Err("field amount is not a valid Integer".to_string())
+        );
+        assert_eq!(
+            check_event(&declared, "transfer", Some(1), br#"{"to": "bob", "amount": 2, "memo": "hi"}"#),
+            Err("field memo is not declared in version 1 of event transfer".to_string())
+        );
+        assert_eq!(check_event(&declared, "transfer", None, br#"{"amount": 2}"#), Err("required field to is missing".to_string()));
+        assert_eq!(check_event(&declared, "transfer", Some(3), b"{}"), Err("version 3 of event transfer is not declared".to_string()));
+        assert_eq!(check_event(&declared, "refund", None, b"{}"), Err("event refund is not declared".to_string()));
+        assert_eq!(check_event(&declared, "transfer", None, b"not json"), Err("payload is not a JSON object".to_string()));
+
+        // A dot without declared events emits whatever it likes
+        assert_eq!(check_event(&[], "anything", None, b"not json"), Ok(()));
+    }
+
+    #[test]
+    fn test_array_elements_and_strictness_names() {
+        let mut tags = field("tags", "Array", true);
+        tags.field_type.as_mut().unwrap().generic_params.push(AbiType {
+            type_name: "UUID".to_string(),
+            ..Default::default()
+        });
+        let declared = vec![event("tagged", 1, vec![tags])];
+
+        assert!(check_event(&declared, "tagged", None, br#"{"tags": ["67e55044-10b1-426f-9247-bb680e5fe0c8"]}"#).is_ok());
+        assert!(check_event(&declared, "tagged", None, br#"{"tags": ["not-a-uuid"]}"#).is_err());
+
+        assert_eq!("Reject".parse(), Ok(EventSchemaStrictness::Reject));
+        assert_eq!("off".parse(), Ok(EventSchemaStrictness::Off));
+        assert!("loose".parse::<EventSchemaStrictness>().is_err());
+    }
+}
@@ -14,11 +14,13 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-//! ABI generator - generates ABIs from dot source code
+//! ABI generator - generates ABIs from dot source code or transpiled WASM modules
 
+use dotvm_compiler::wasm::WasmParser;
 use thiserror::Error;
 use tracing::{error, info, instrument};
 
+use super::wasm::generate_wasm_abi;
 use crate::proto::vm_service::{AbiField, AbiType, DotAbi, GenerateAbiRequest, GenerateAbiResponse, ParaDotDependency, PermissionConfig, UiHints};
 
 #[derive(Error, Debug)]
@@ -29,6 +31,8 @@ pub enum GeneratorError {
     InvalidSyntax(String),
     #[error("Type inference failed: {0}")]
     TypeInferenceFailed(String),
+    #[error("Invalid WASM module: {0}")]
+    InvalidWasm(String),
 }
 
 /// ABI generator creates ABIs from dot source code
@@ -41,6 +45,28 @@ impl AbiGenerator {
         Self {}
     }
 
+    /// Generate an ABI from the request's WASM module when present, otherwise from its source
+    pub async fn generate(&self, request: GenerateAbiRequest) -> Result<GenerateAbiResponse, GeneratorError> {
+        if request.wasm_module.is_empty() { self.generate_from_source(request).await } else { self.generate_from_wasm(request) }
+    }
+
+    #[instrument(skip(self, request))]
+    pub fn generate_from_wasm(&self, request: GenerateAbiRequest) -> Result<GenerateAbiResponse, GeneratorError> {
+        info!("Generating ABI from WASM module ({} bytes)", request.wasm_module.len());
+
+        let module = WasmParser::new().parse(&request.wasm_module).map_err(|e| GeneratorError::InvalidWasm(e.to_string()))?;
+        let dot_name = if request.dot_name.is_empty() { "UnknownDot" } else { request.dot_name.as_str() };
+        let generated = generate_wasm_abi(&module, dot_name);
+
+        Ok(GenerateAbiResponse {
+            success: true,
+            abi: Some(generated.abi),
+            error_message: String::new(),
+            warnings: generated.warnings,
+            canonical_json: generated.canonical_json,
+        })
+    }
+
     #[instrument(skip(self, request))]
     pub async fn generate_from_source(&self, request: GenerateAbiRequest) -> Result<GenerateAbiResponse, GeneratorError> {
         info!("Generating ABI from source ({} chars)", request.dot_source.len());
@@ -56,6 +82,7 @@ impl AbiGenerator {
             abi: Some(abi),
             error_message: String::new(),
             warnings: vec![], // TODO: Add warnings from parsing
+            canonical_json: String::new(),
         })
     }
 
@@ -91,6 +118,11 @@ impl AbiGenerator {
             paradots: parsed_dot.paradots.clone(),
             ui_hints: None,
             permissions: parsed_dot.permissions.clone(),
+            operations: vec![],
+            state_schema: vec![],
+            dependencies: vec![],
+            deterministic: false,
+            events: vec![],
         };
 
         // Generate UI hints if requested
@@ -16,9 +16,22 @@
 
 //! ABI service - handles ABI generation, validation, and registry
 
+pub mod events;
 pub mod generator;
 pub mod registry;
 pub mod service;
 pub mod validator;
+pub mod wasm;
 
 pub use service::AbiService;
+
+use crate::proto::vm_service::PermissionConfig;
+use dotvm_core::vm::dot_database::DbAccessPolicy;
+
+/// Database access a dot's ABI declares; a dot without permissions may not touch the database
+pub fn database_access_policy(permissions: Option<&PermissionConfig>) -> DbAccessPolicy {
+    permissions
+        .into_iter()
+        .flat_map(|permissions| &permissions.collections)
+        .fold(DbAccessPolicy::new(), |policy, access| policy.grant(access.collection.clone(), access.read, access.write))
+}
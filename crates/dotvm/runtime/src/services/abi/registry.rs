@@ -15,13 +15,38 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 //! ABI registry - stores and manages ABI versions
-
+//!
+//! Every registered ABI is a document in [`ABI_COLLECTION`], so the history survives restarts
+//! when the registry is backed by persistent document storage. Versions are numbered per dot
+//! from 1. Deleting a dot archives its ABIs instead of removing them, so the inputs and outputs
+//! of its past executions can still be interpreted.
+
+use base64::{Engine as _, engine::general_purpose};
+use dotdb_core::document::{CollectionManager, DocumentError, DocumentId, create_in_memory_collection_manager};
+use prost::Message;
+use serde_json::{Value, json};
 use std::collections::HashMap;
-use std::sync::RwLock;
+use std::sync::{Arc, Mutex, RwLock};
 use thiserror::Error;
-use tracing::{error, info, instrument};
+use tracing::{info, instrument};
+
+use crate::proto::vm_service::{
+    AbiEvent, AbiField, AbiType, AbiVersionInfo, DotAbi, GetDotAbiResponse, PermissionConfig, RegisterAbiRequest, RegisterAbiResponse, ValidateAbiRequest, ValidationError, ValidationWarning,
+};
+
+use super::validator::{AbiValidator, ValidatorError};
+
+/// Namespace of the runtime's own document collections
+pub const ABI_NAMESPACE: &str = "dotvm";
+
+/// Collection holding one document per registered ABI version
+pub const ABI_COLLECTION: &str = "dot_abis";
+
+/// Warning code of an incompatible change against the previous version
+pub const BREAKING_CHANGE: &str = "BREAKING_CHANGE";
 
-use crate::proto::vm_service::{DotAbi, GetDotAbiRequest, GetDotAbiResponse, RegisterAbiRequest, RegisterAbiResponse};
+/// Error code of a change to an already declared event version
+pub const INCOMPATIBLE_EVENT_CHANGE: &str = "INCOMPATIBLE_EVENT_CHANGE";
 
 #[derive(Error, Debug)]
 pub enum RegistryError {
@@ -31,142 +56,581 @@ pub enum RegistryError {
     AbiAlreadyExists(String),
     #[error("Invalid ABI version: {0}")]
     InvalidVersion(String),
+    #[error("ABI storage failed: {0}")]
+    Storage(#[from] DocumentError),
+    #[error("Stored ABI is corrupt: {0}")]
+    Corrupt(String),
+    #[error(transparent)]
+    Validation(#[from] ValidatorError),
 }
 
 /// ABI registry stores and manages ABI versions
 pub struct AbiRegistry {
-    abis: RwLock<HashMap<String, HashMap<String, StoredAbi>>>, // dot_id -> version -> abi
+    documents: CollectionManager,
+    validator: Arc<AbiValidator>,
+    /// Held while assigning a version, so concurrent registrations for a dot get distinct versions
+    writes: Mutex<()>,
+    /// Events declared by each dot's latest ABI, looked up for every emitted event
+    events: RwLock<HashMap<String, Arc<[AbiEvent]>>>,
+    /// Told the dot's id whenever it registers a new latest ABI
+    listeners: RwLock<Vec<RegistrationListener>>,
 }
 
+type RegistrationListener = Box<dyn Fn(&str) + Send + Sync>;
+
 #[derive(Clone, Debug)]
 struct StoredAbi {
+    id: DocumentId,
+    version: u32,
     abi: DotAbi,
     registered_at: u64,
     registrar_id: String,
+    archived: bool,
+    breaking_changes: Vec<String>,
+}
+
+impl StoredAbi {
+    fn to_document(&self, dot_id: &str) -> Value {
+        json!({
+            "dot_id": dot_id,
+            "version": self.version,
+            "abi_version": self.abi.version,
+            "registered_at": self.registered_at,
+            "registrar_id": self.registrar_id,
+            "archived": self.archived,
+            "breaking_changes": self.breaking_changes,
+            "abi": general_purpose::STANDARD.encode(self.abi.encode_to_vec()),
+        })
+    }
+
+    fn from_document(id: DocumentId, document: &Value) -> Result<Self, RegistryError> {
+        let corrupt = |what: &str| RegistryError::Corrupt(format!("document {} has no valid {}", id, what));
+        let encoded = document["abi"].as_str().ok_or_else(|| corrupt("abi"))?;
+        let bytes = general_purpose::STANDARD.decode(encoded).map_err(|_| corrupt("abi"))?;
+        let abi = DotAbi::decode(bytes.as_slice()).map_err(|_| corrupt("abi"))?;
+        Ok(Self {
+            version: document["version"].as_u64().and_then(|version| u32::try_from(version).ok()).ok_or_else(|| corrupt("version"))?,
+            abi,
+            registered_at: document["registered_at"].as_u64().unwrap_or_default(),
+            registrar_id: document["registrar_id"].as_str().unwrap_or_default().to_string(),
+            archived: document["archived"].as_bool().unwrap_or_default(),
+            breaking_changes: document["breaking_changes"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|change| change.as_str().map(str::to_string))
+                .collect(),
+            id,
+        })
+    }
+
+    fn info(&self) -> AbiVersionInfo {
+        AbiVersionInfo {
+            registry_version: self.version,
+            abi_version: self.abi.version.clone(),
+            registered_at: self.registered_at,
+            registrar_id: self.registrar_id.clone(),
+            archived: self.archived,
+            breaking_changes: self.breaking_changes.clone(),
+        }
+    }
 }
 
 impl AbiRegistry {
+    /// Keeps ABIs in memory only; see [`Self::with_documents`] to persist them
     pub fn new() -> Self {
-        Self { abis: RwLock::new(HashMap::new()) }
+        let documents = create_in_memory_collection_manager().expect("in-memory document storage cannot fail to open");
+        Self::with_documents(documents, Arc::new(AbiValidator::new()))
+    }
+
+    /// Keeps ABIs in `documents`, e.g. the [`ABI_NAMESPACE`] of the runtime's document store,
+    /// and checks them with `validator` before registering
+    pub fn with_documents(documents: CollectionManager, validator: Arc<AbiValidator>) -> Self {
+        Self {
+            documents,
+            validator,
+            writes: Mutex::new(()),
+            events: RwLock::new(HashMap::new()),
+            listeners: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Calls `listener` with the dot's id after every registration of a new latest ABI,
+    /// while further registrations for any dot wait
+    pub fn on_registered(&self, listener: impl Fn(&str) + Send + Sync + 'static) {
+        self.listeners.write().unwrap_or_else(|e| e.into_inner()).push(Box::new(listener));
     }
 
+    /// Registered versions of a dot's ABI, oldest first
+    fn versions(&self, dot_id: &str) -> Result<Vec<StoredAbi>, RegistryError> {
+        if !self.documents.collection_exists(ABI_COLLECTION)? {
+            return Ok(Vec::new());
+        }
+        let mut versions = self
+            .documents
+            .find_by_field(ABI_COLLECTION, "dot_id", &json!(dot_id))?
+            .into_iter()
+            .map(|(id, document)| StoredAbi::from_document(id, &document))
+            .collect::<Result<Vec<_>, _>>()?;
+        versions.sort_by_key(|stored| stored.version);
+        Ok(versions)
+    }
+
+    /// Events declared by the latest ABI of a dot; empty when it has no ABI
+    pub fn declared_events(&self, dot_id: &str) -> Result<Arc<[AbiEvent]>, RegistryError> {
+        if let Some(events) = self.events.read().unwrap_or_else(|e| e.into_inner()).get(dot_id) {
+            return Ok(Arc::clone(events));
+        }
+        // Registrations invalidate the cache under the same lock, so no stale entry is stored
+        let _writes = self.writes.lock().unwrap_or_else(|e| e.into_inner());
+        let events: Arc<[AbiEvent]> = self.versions(dot_id)?.pop().map(|stored| stored.abi.events).unwrap_or_default().into();
+        self.events.write().unwrap_or_else(|e| e.into_inner()).insert(dot_id.to_string(), Arc::clone(&events));
+        Ok(events)
+    }
+
+    /// Permissions declared by the latest ABI of a dot; `None` when it has no ABI or the ABI declares none
+    pub fn declared_permissions(&self, dot_id: &str) -> Result<Option<PermissionConfig>, RegistryError> {
+        Ok(self.versions(dot_id)?.pop().and_then(|stored| stored.abi.permissions))
+    }
+
+    /// Returns the latest ABI of a dot, or the one with the given registry version ("3") or
+    /// declared version ("1.2.0"). ABIs of deleted dots are still returned, marked as archived.
     #[instrument(skip(self))]
     pub async fn get_abi(&self, dot_id: &str, version: Option<&str>) -> Result<GetDotAbiResponse, RegistryError> {
         info!("Getting ABI for dot: {} version: {:?}", dot_id, version);
 
-        let abis = self.abis.read().unwrap();
-
-        let dot_abis = abis.get(dot_id).ok_or_else(|| RegistryError::AbiNotFound(dot_id.to_string()))?;
-
-        let stored_abi = if let Some(version) = version {
-            dot_abis.get(version).ok_or_else(|| RegistryError::AbiNotFound(format!("{}:{}", dot_id, version)))?
-        } else {
-            // Get latest version
-            dot_abis.values().max_by_key(|abi| abi.registered_at).ok_or_else(|| RegistryError::AbiNotFound(dot_id.to_string()))?
+        let versions = self.versions(dot_id)?;
+        let stored = match version {
+            None => versions.last(),
+            Some(version) => match version.parse::<u32>() {
+                Ok(registry_version) => versions.iter().find(|stored| stored.version == registry_version),
+                Err(_) => versions.iter().rev().find(|stored| stored.abi.version == version),
+            },
         };
+        let stored = stored.ok_or_else(|| match version {
+            Some(version) => RegistryError::AbiNotFound(format!("{}:{}", dot_id, version)),
+            None => RegistryError::AbiNotFound(dot_id.to_string()),
+        })?;
 
         Ok(GetDotAbiResponse {
             success: true,
-            abi: Some(stored_abi.abi.clone()),
+            abi: Some(stored.abi.clone()),
             error_message: String::new(),
+            registry_version: stored.version,
+            archived: stored.archived,
         })
     }
 
+    /// Validates the ABI with the rules of `validate_abi` and stores it as the dot's next version
+    ///
+    /// An invalid ABI is not stored; the response lists its errors. Changes that break callers
+    /// of the previous version are allowed but reported as `BREAKING_CHANGE` warnings. Events
+    /// may gain optional fields, but any other change to their payload needs a new event
+    /// version; an ABI that changes a declared version is refused with `INCOMPATIBLE_EVENT_CHANGE`.
+    /// Registering the dot's latest ABI again returns its existing version.
     #[instrument(skip(self, request))]
     pub async fn register_abi(&self, request: RegisterAbiRequest) -> Result<RegisterAbiResponse, RegistryError> {
         info!("Registering ABI for dot: {}", request.dot_id);
 
         let abi = request.abi.ok_or_else(|| RegistryError::AbiNotFound("No ABI provided".to_string()))?;
 
-        // Validate version format
-        if !self.is_valid_version(&abi.version) {
-            return Err(RegistryError::InvalidVersion(abi.version.clone()));
+        let validation = self
+            .validator
+            .validate_abi(ValidateAbiRequest {
+                abi: Some(abi.clone()),
+                strict_mode: false,
+            })
+            .await?;
+        if !validation.valid {
+            return Ok(RegisterAbiResponse {
+                success: false,
+                abi_version: abi.version,
+                error_message: format!("ABI failed validation with {} error(s)", validation.errors.len()),
+                registry_version: 0,
+                warnings: validation.warnings,
+                errors: validation.errors,
+            });
+        }
+        let mut warnings = validation.warnings;
+
+        let _writes = self.writes.lock().unwrap_or_else(|e| e.into_inner());
+        let versions = self.versions(&request.dot_id)?;
+        let previous = versions.last();
+
+        if let Some(previous) = previous.filter(|previous| previous.abi == abi) {
+            return Ok(RegisterAbiResponse {
+                success: true,
+                abi_version: abi.version,
+                error_message: String::new(),
+                registry_version: previous.version,
+                warnings,
+                errors: Vec::new(),
+            });
+        }
+        if versions.iter().any(|stored| stored.abi.version == abi.version) {
+            return Err(RegistryError::AbiAlreadyExists(format!("{}:{}", request.dot_id, abi.version)));
+        }
+
+        let (incompatible, event_breaking) = previous.map(|previous| event_changes(&previous.abi.events, &abi.events)).unwrap_or_default();
+        if !incompatible.is_empty() {
+            return Ok(RegisterAbiResponse {
+                success: false,
+                abi_version: abi.version,
+                error_message: format!("ABI changes {} event field(s) without declaring a new event version", incompatible.len()),
+                registry_version: 0,
+                warnings,
+                errors: incompatible
+                    .into_iter()
+                    .map(|(field, message)| ValidationError {
+                        field,
+                        message,
+                        error_code: INCOMPATIBLE_EVENT_CHANGE.to_string(),
+                    })
+                    .collect(),
+            });
         }
 
-        let stored_abi = StoredAbi {
-            abi: abi.clone(),
+        let mut breaking = previous.map(|previous| breaking_changes(&previous.abi, &abi)).unwrap_or_default();
+        breaking.extend(event_breaking);
+        warnings.extend(breaking.iter().map(|(field, message)| ValidationWarning {
+            field: field.clone(),
+            message: message.clone(),
+            warning_code: BREAKING_CHANGE.to_string(),
+        }));
+
+        let stored = StoredAbi {
+            id: DocumentId::new(),
+            version: previous.map_or(1, |previous| previous.version + 1),
+            abi,
             registered_at: chrono::Utc::now().timestamp() as u64,
             registrar_id: request.registrar_id,
+            archived: false,
+            breaking_changes: breaking.into_iter().map(|(field, message)| format!("{}: {}", field, message)).collect(),
         };
-
-        // Store ABI
-        {
-            let mut abis = self.abis.write().unwrap();
-            let dot_abis = abis.entry(request.dot_id.clone()).or_insert_with(HashMap::new);
-
-            // Check if version already exists
-            if dot_abis.contains_key(&abi.version) {
-                return Err(RegistryError::AbiAlreadyExists(format!("{}:{}", request.dot_id, abi.version)));
-            }
-
-            dot_abis.insert(abi.version.clone(), stored_abi);
+        self.documents.put_value(ABI_COLLECTION, &stored.id, stored.to_document(&request.dot_id))?;
+        self.events.write().unwrap_or_else(|e| e.into_inner()).remove(&request.dot_id);
+        for listener in self.listeners.read().unwrap_or_else(|e| e.into_inner()).iter() {
+            listener(&request.dot_id);
         }
 
-        info!("Successfully registered ABI for dot: {} version: {}", request.dot_id, abi.version);
+        info!("Registered ABI version {} ({}) for dot: {}", stored.version, stored.abi.version, request.dot_id);
 
         Ok(RegisterAbiResponse {
             success: true,
-            abi_version: abi.version,
+            abi_version: stored.abi.version,
             error_message: String::new(),
+            registry_version: stored.version,
+            warnings,
+            errors: Vec::new(),
         })
     }
 
-    /// Get all versions of an ABI for a dot
-    pub async fn get_abi_versions(&self, dot_id: &str) -> Result<Vec<String>, RegistryError> {
-        let abis = self.abis.read().unwrap();
-
-        let dot_abis = abis.get(dot_id).ok_or_else(|| RegistryError::AbiNotFound(dot_id.to_string()))?;
-
-        let mut versions: Vec<String> = dot_abis.keys().cloned().collect();
-        versions.sort_by(|a, b| self.compare_versions(a, b));
+    /// Lists every registered version of a dot's ABI, oldest first
+    pub async fn list_versions(&self, dot_id: &str) -> Result<Vec<AbiVersionInfo>, RegistryError> {
+        let versions = self.versions(dot_id)?;
+        if versions.is_empty() {
+            return Err(RegistryError::AbiNotFound(dot_id.to_string()));
+        }
+        Ok(versions.iter().map(StoredAbi::info).collect())
+    }
 
-        Ok(versions)
+    /// Marks a deleted dot's ABIs as archived, keeping them readable; returns how many were archived
+    #[instrument(skip(self))]
+    pub async fn archive_dot(&self, dot_id: &str) -> Result<usize, RegistryError> {
+        let _writes = self.writes.lock().unwrap_or_else(|e| e.into_inner());
+        let mut archived = 0;
+        for mut stored in self.versions(dot_id)?.into_iter().filter(|stored| !stored.archived) {
+            stored.archived = true;
+            self.documents.put_value(ABI_COLLECTION, &stored.id, stored.to_document(dot_id))?;
+            archived += 1;
+        }
+        if archived > 0 {
+            info!("Archived {} ABI version(s) of deleted dot: {}", archived, dot_id);
+        }
+        Ok(archived)
     }
 
     /// Check if an ABI exists
     pub async fn abi_exists(&self, dot_id: &str, version: Option<&str>) -> bool {
-        let abis = self.abis.read().unwrap();
+        self.get_abi(dot_id, version).await.is_ok()
+    }
+}
+
+/// Changes from `previous` to `next` that break callers built against `previous`,
+/// as (field, message) pairs
+fn breaking_changes(previous: &DotAbi, next: &DotAbi) -> Vec<(String, String)> {
+    let mut changes = Vec::new();
+    compare_fields(&previous.inputs, &next.inputs, "inputs", "Input", true, &mut changes);
+
+    let next_operations: HashMap<&str, _> = next.operations.iter().map(|operation| (operation.name.as_str(), operation)).collect();
+    for operation in &previous.operations {
+        let path = format!("operations.{}", operation.name);
+        let Some(next_operation) = next_operations.get(operation.name.as_str()) else {
+            changes.push((path, format!("Operation {} was removed", operation.name)));
+            continue;
+        };
+        compare_fields(&operation.params, &next_operation.params, &format!("{}.params", path), "Parameter", true, &mut changes);
+        compare_fields(&operation.returns, &next_operation.returns, &format!("{}.returns", path), "Return value", false, &mut changes);
+    }
+    changes
+}
 
-        if let Some(dot_abis) = abis.get(dot_id) {
-            if let Some(version) = version { dot_abis.contains_key(version) } else { !dot_abis.is_empty() }
-        } else {
-            false
+/// ABI changes as (field, message) pairs
+type Changes = Vec<(String, String)>;
+
+/// Changes from `previous` to `next` declared events: first the changes to
+/// a version both declare, which subscribers could not tell apart, then the breaking changes, i.e.
+/// removed versions and new versions that are incompatible with the one before them
+fn event_changes(previous: &[AbiEvent], next: &[AbiEvent]) -> (Changes, Changes) {
+    let declared = |events: &[AbiEvent], name: &str, version: u32| events.iter().any(|event| event.name == name && event.version == version);
+    let mut incompatible = Vec::new();
+    let mut breaking = Vec::new();
+
+    for event in previous {
+        let path = format!("events.{}.v{}", event.name, event.version);
+        match next.iter().find(|next_event| next_event.name == event.name && next_event.version == event.version) {
+            Some(next_event) => compare_fields(&event.fields, &next_event.fields, &format!("{}.fields", path), "Field", true, &mut incompatible),
+            None => breaking.push((path, format!("Event {} version {} was removed", event.name, event.version))),
         }
     }
 
-    /// Update an existing ABI (creates new version)
-    pub async fn update_abi(&self, dot_id: &str, new_abi: DotAbi, registrar_id: String) -> Result<RegisterAbiResponse, RegistryError> {
-        // This is essentially the same as register_abi but with version increment logic
-        let request = RegisterAbiRequest {
-            dot_id: dot_id.to_string(),
-            abi: Some(new_abi),
-            registrar_id,
+    for event in next.iter().filter(|event| !declared(previous, &event.name, event.version)) {
+        let before = previous
+            .iter()
+            .filter(|previous_event| previous_event.name == event.name && previous_event.version < event.version)
+            .max_by_key(|previous_event| previous_event.version);
+        let Some(before) = before else {
+            continue;
         };
+        let mut changes = Vec::new();
+        compare_fields(&before.fields, &event.fields, "", "Field", true, &mut changes);
+        if !changes.is_empty() {
+            breaking.push((
+                format!("events.{}.v{}", event.name, event.version),
+                format!("Event {} version {} is incompatible with version {}", event.name, event.version, before.version),
+            ));
+        }
+    }
+    (incompatible, breaking)
+}
 
-        self.register_abi(request).await
+/// Reports removed and retyped fields, and with `new_required` fields callers must now supply
+fn compare_fields(previous: &[AbiField], next: &[AbiField], path: &str, kind: &str, new_required: bool, changes: &mut Vec<(String, String)>) {
+    let next_fields: HashMap<&str, &AbiField> = next.iter().map(|field| (field.name.as_str(), field)).collect();
+    for field in previous {
+        let field_path = format!("{}.{}", path, field.name);
+        match next_fields.get(field.name.as_str()) {
+            None => changes.push((field_path, format!("{} {} was removed", kind, field.name))),
+            Some(next_field) => {
+                let (before, after) = (type_name(field.field_type.as_ref()), type_name(next_field.field_type.as_ref()));
+                if before != after {
+                    changes.push((field_path, format!("{} {} changed type from {} to {}", kind, field.name, before, after)));
+                }
+            }
+        }
     }
 
-    // Private helper methods
-    fn is_valid_version(&self, version: &str) -> bool {
-        // Simple semantic version validation
-        let parts: Vec<&str> = version.split('.').collect();
-        parts.len() == 3 && parts.iter().all(|part| part.parse::<u32>().is_ok())
+    if new_required {
+        let previous_names: Vec<&str> = previous.iter().map(|field| field.name.as_str()).collect();
+        for field in next.iter().filter(|field| field.required && !previous_names.contains(&field.name.as_str())) {
+            changes.push((format!("{}.{}", path, field.name), format!("{} {} was added as required", kind, field.name)));
+        }
     }
+}
 
-    fn compare_versions(&self, a: &str, b: &str) -> std::cmp::Ordering {
-        let a_parts: Vec<u32> = a.split('.').filter_map(|s| s.parse().ok()).collect();
-        let b_parts: Vec<u32> = b.split('.').filter_map(|s| s.parse().ok()).collect();
+/// Type name with its generic parameters, e.g. `Array<String>`
+fn type_name(field_type: Option<&AbiType>) -> String {
+    let Some(field_type) = field_type else {
+        return "none".to_string();
+    };
+    if field_type.generic_params.is_empty() {
+        return field_type.type_name.clone();
+    }
+    let params: Vec<String> = field_type.generic_params.iter().map(|param| type_name(Some(param))).collect();
+    format!("{}<{}>", field_type.type_name, params.join(", "))
+}
 
-        for i in 0..3 {
-            let a_part = a_parts.get(i).unwrap_or(&0);
-            let b_part = b_parts.get(i).unwrap_or(&0);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::vm_service::AbiOperation;
+    use dotdb_core::document::create_persistent_collection_manager;
+
+    fn field(name: &str, type_name: &str, required: bool) -> AbiField {
+        AbiField {
+            name: name.to_string(),
+            field_type: Some(AbiType {
+                type_name: type_name.to_string(),
+                ..Default::default()
+            }),
+            required,
+            ..Default::default()
+        }
+    }
 
-            match a_part.cmp(b_part) {
-                std::cmp::Ordering::Equal => continue,
-                other => return other,
-            }
+    fn abi(version: &str, operations: Vec<AbiOperation>) -> DotAbi {
+        DotAbi {
+            dot_name: "wallet".to_string(),
+            version: version.to_string(),
+            description: "Wallet dot".to_string(),
+            inputs: vec![field("account", "String", true)],
+            operations,
+            ..Default::default()
+        }
+    }
+
+    fn operation(name: &str, params: Vec<AbiField>) -> AbiOperation {
+        AbiOperation {
+            name: name.to_string(),
+            params,
+            ..Default::default()
+        }
+    }
+
+    fn request(abi: DotAbi) -> RegisterAbiRequest {
+        RegisterAbiRequest {
+            dot_id: "dot-1".to_string(),
+            abi: Some(abi),
+            registrar_id: "tester".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_versions_are_assigned_and_retrievable() {
+        let registry = AbiRegistry::new();
+        let first = registry.register_abi(request(abi("1.0.0", vec![operation("transfer", vec![])]))).await.unwrap();
+        let second = registry
+            .register_abi(request(abi("1.1.0", vec![operation("transfer", vec![]), operation("balance", vec![])])))
+            .await
+            .unwrap();
+        assert_eq!((first.registry_version, second.registry_version), (1, 2));
+
+        let latest = registry.get_abi("dot-1", None).await.unwrap();
+        assert_eq!(latest.registry_version, 2);
+        assert_eq!(latest.abi.unwrap().version, "1.1.0");
+        assert_eq!(registry.get_abi("dot-1", Some("1")).await.unwrap().abi.unwrap().version, "1.0.0");
+        assert_eq!(registry.get_abi("dot-1", Some("1.1.0")).await.unwrap().registry_version, 2);
+        assert!(matches!(registry.get_abi("dot-1", Some("3")).await, Err(RegistryError::AbiNotFound(_))));
+
+        let versions = registry.list_versions("dot-1").await.unwrap();
+        assert_eq!(versions.iter().map(|info| info.registry_version).collect::<Vec<_>>(), vec![1, 2]);
+        assert!(versions.iter().all(|info| info.registrar_id == "tester" && info.breaking_changes.is_empty()));
+
+        // The same ABI again is not a new version; another ABI may not reuse a declared version
+        assert_eq!(
+            registry
+                .register_abi(request(abi("1.1.0", vec![operation("transfer", vec![]), operation("balance", vec![])])))
+                .await
+                .unwrap()
+                .registry_version,
+            2
+        );
+        assert!(matches!(registry.register_abi(request(abi("1.1.0", vec![]))).await, Err(RegistryError::AbiAlreadyExists(_))));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_abi_is_rejected_with_validation_errors() {
+        let registry = AbiRegistry::new();
+        let mut invalid = abi("1.0", vec![]);
+        invalid.inputs.push(field("amount", "Money", true));
+
+        let response = registry.register_abi(request(invalid)).await.unwrap();
+        assert!(!response.success);
+        let codes: Vec<&str> = response.errors.iter().map(|error| error.error_code.as_str()).collect();
+        assert_eq!(codes, vec!["INVALID_VERSION", "UNKNOWN_TYPE"]);
+        assert!(matches!(registry.list_versions("dot-1").await, Err(RegistryError::AbiNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_breaking_changes_are_flagged_but_registered() {
+        let registry = AbiRegistry::new();
+        let params = vec![field("to", "String", true), field("amount", "Integer", true)];
+        registry
+            .register_abi(request(abi("1.0.0", vec![operation("transfer", params), operation("freeze", vec![])])))
+            .await
+            .unwrap();
+
+        let changed = vec![field("to", "String", true), field("amount", "Float", true), field("memo", "String", false)];
+        let response = registry.register_abi(request(abi("2.0.0", vec![operation("transfer", changed)]))).await.unwrap();
+        assert!(response.success);
+        assert_eq!(response.registry_version, 2);
+
+        let breaking: Vec<(&str, &str)> = response
+            .warnings
+            .iter()
+            .filter(|warning| warning.warning_code == BREAKING_CHANGE)
+            .map(|warning| (warning.field.as_str(), warning.message.as_str()))
+            .collect();
+        assert_eq!(
+            breaking,
+            vec![
+                ("operations.transfer.params.amount", "Parameter amount changed type from Integer to Float"),
+                ("operations.freeze", "Operation freeze was removed"),
+            ]
+        );
+        assert_eq!(registry.list_versions("dot-1").await.unwrap()[1].breaking_changes.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_event_schemas_evolve_through_new_versions() {
+        let registry = AbiRegistry::new();
+        let event = |version: u32, fields: Vec<AbiField>| AbiEvent {
+            name: "transferred".to_string(),
+            version,
+            fields,
+            ..Default::default()
+        };
+        let with_events = |version: &str, events: Vec<AbiEvent>| DotAbi { events, ..abi(version, vec![]) };
+
+        let v1 = vec![field("to", "String", true), field("amount", "Integer", true)];
+        registry.register_abi(request(with_events("1.0.0", vec![event(1, v1.clone())]))).await.unwrap();
+        assert_eq!(registry.declared_events("dot-1").unwrap().len(), 1);
+
+        // Optional fields may be added to a declared version
+        let mut with_memo = v1;
+        with_memo.push(field("memo", "String", false));
+        let response = registry.register_abi(request(with_events("1.1.0", vec![event(1, with_memo)]))).await.unwrap();
+        assert!(response.success && response.warnings.iter().all(|warning| warning.warning_code != BREAKING_CHANGE));
+        assert_eq!(registry.declared_events("dot-1").unwrap()[0].fields.len(), 3);
+
+        // Retyping a field of a declared version is refused
+        let retyped = vec![field("to", "String", true), field("amount", "Float", true), field("memo", "String", false)];
+        let response = registry.register_abi(request(with_events("1.2.0", vec![event(1, retyped.clone())]))).await.unwrap();
+        assert!(!response.success);
+        let errors: Vec<(&str, &str)> = response.errors.iter().map(|error| (error.field.as_str(), error.error_code.as_str())).collect();
+        assert_eq!(errors, vec![("events.transferred.v1.fields.amount", INCOMPATIBLE_EVENT_CHANGE)]);
+        assert_eq!(registry.list_versions("dot-1").await.unwrap().len(), 2);
+
+        // As a new version it is accepted, with a warning once the old version is dropped
+        let response = registry.register_abi(request(with_events("2.0.0", vec![event(2, retyped)]))).await.unwrap();
+        assert!(response.success);
+        let breaking: Vec<&str> = response
+            .warnings
+            .iter()
+            .filter(|warning| warning.warning_code == BREAKING_CHANGE)
+            .map(|warning| warning.message.as_str())
+            .collect();
+        assert_eq!(breaking, vec!["Event transferred version 1 was removed", "Event transferred version 2 is incompatible with version 1"]);
+        assert_eq!(registry.declared_events("dot-1").unwrap()[0].version, 2);
+        assert!(registry.declared_events("dot-2").unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_archived_history_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let registry = AbiRegistry::with_documents(create_persistent_collection_manager(dir.path(), None).unwrap(), Arc::new(AbiValidator::new()));
+            registry.register_abi(request(abi("1.0.0", vec![]))).await.unwrap();
+            registry.register_abi(request(abi("1.0.1", vec![operation("ping", vec![])]))).await.unwrap();
+            assert_eq!(registry.archive_dot("dot-1").await.unwrap(), 2);
+            assert_eq!(registry.archive_dot("dot-1").await.unwrap(), 0);
+            registry.documents.flush().unwrap();
         }
 
-        std::cmp::Ordering::Equal
+        let registry = AbiRegistry::with_documents(create_persistent_collection_manager(dir.path(), None).unwrap(), Arc::new(AbiValidator::new()));
+        let first = registry.get_abi("dot-1", Some("1")).await.unwrap();
+        assert!(first.archived);
+        assert_eq!(first.abi.unwrap().version, "1.0.0");
+        assert!(registry.list_versions("dot-1").await.unwrap().iter().all(|info| info.archived));
     }
 }
@@ -16,14 +16,18 @@
 
 //! ABI service implementation
 
+use dotdb_core::document::CollectionManager;
 use std::sync::Arc;
 use tonic::{Request, Response, Result as TonicResult, Status};
 use tracing::{error, info, instrument};
 
-use crate::proto::vm_service::{GenerateAbiRequest, GenerateAbiResponse, GetDotAbiRequest, GetDotAbiResponse, RegisterAbiRequest, RegisterAbiResponse, ValidateAbiRequest, ValidateAbiResponse};
+use crate::proto::vm_service::{
+    GenerateAbiRequest, GenerateAbiResponse, GetDotAbiRequest, GetDotAbiResponse, ListAbiVersionsRequest, ListAbiVersionsResponse, RegisterAbiRequest, RegisterAbiResponse, ValidateAbiRequest,
+    ValidateAbiResponse,
+};
 
-use super::generator::AbiGenerator;
-use super::registry::AbiRegistry;
+use super::generator::{AbiGenerator, GeneratorError};
+use super::registry::{AbiRegistry, RegistryError};
 use super::validator::AbiValidator;
 
 /// ABI service handles all ABI-related operations
@@ -42,17 +46,36 @@ impl AbiService {
         }
     }
 
+    /// Persists registered ABIs in `documents` instead of memory
+    pub fn with_documents(documents: CollectionManager) -> Self {
+        let validator = Arc::new(AbiValidator::new());
+        Self {
+            generator: Arc::new(AbiGenerator::new()),
+            registry: Arc::new(AbiRegistry::with_documents(documents, Arc::clone(&validator))),
+            validator,
+        }
+    }
+
+    /// The registry, also consulted for the event schemas of emitted dot events
+    pub fn registry(&self) -> Arc<AbiRegistry> {
+        Arc::clone(&self.registry)
+    }
+
     #[instrument(skip(self, request))]
     pub async fn get_dot_abi(&self, request: Request<GetDotAbiRequest>) -> TonicResult<Response<GetDotAbiResponse>> {
         let req = request.into_inner();
 
         info!("Getting ABI for dot: {}", req.dot_id);
 
+        if req.dot_id.is_empty() {
+            return Err(Status::invalid_argument("dot_id cannot be empty"));
+        }
+
         let result = self
             .registry
             .get_abi(&req.dot_id, if req.version.is_empty() { None } else { Some(req.version.as_str()) })
             .await
-            .map_err(|e| Status::not_found(format!("ABI not found: {}", e)))?;
+            .map_err(registry_status)?;
 
         Ok(Response::new(result))
     }
@@ -72,9 +95,12 @@ impl AbiService {
     pub async fn generate_abi(&self, request: Request<GenerateAbiRequest>) -> TonicResult<Response<GenerateAbiResponse>> {
         let req = request.into_inner();
 
-        info!("Generating ABI from dot source ({} chars)", req.dot_source.len());
+        info!("Generating ABI ({} source chars, {} WASM bytes)", req.dot_source.len(), req.wasm_module.len());
 
-        let result = self.generator.generate_from_source(req).await.map_err(|e| Status::internal(format!("Generation failed: {}", e)))?;
+        let result = self.generator.generate(req).await.map_err(|e| match e {
+            GeneratorError::InvalidWasm(_) => Status::invalid_argument(format!("Generation failed: {}", e)),
+            _ => Status::internal(format!("Generation failed: {}", e)),
+        })?;
 
         Ok(Response::new(result))
     }
@@ -85,8 +111,46 @@ impl AbiService {
 
         info!("Registering ABI for dot: {}", req.dot_id);
 
-        let result = self.registry.register_abi(req).await.map_err(|e| Status::internal(format!("Registration failed: {}", e)))?;
+        if req.dot_id.is_empty() {
+            return Err(Status::invalid_argument("dot_id cannot be empty"));
+        }
+        if req.abi.is_none() {
+            return Err(Status::invalid_argument("abi is required"));
+        }
+
+        let result = self.registry.register_abi(req).await.map_err(registry_status)?;
 
         Ok(Response::new(result))
     }
+
+    #[instrument(skip(self, request))]
+    pub async fn list_abi_versions(&self, request: Request<ListAbiVersionsRequest>) -> TonicResult<Response<ListAbiVersionsResponse>> {
+        let req = request.into_inner();
+
+        info!("Listing ABI versions for dot: {}", req.dot_id);
+
+        if req.dot_id.is_empty() {
+            return Err(Status::invalid_argument("dot_id cannot be empty"));
+        }
+
+        let versions = self.registry.list_versions(&req.dot_id).await.map_err(registry_status)?;
+
+        Ok(Response::new(ListAbiVersionsResponse { versions }))
+    }
+
+    /// Archives the ABI history of a deleted dot; failures are logged, as the dot is already gone
+    pub async fn archive_dot(&self, dot_id: &str) {
+        if let Err(e) = self.registry.archive_dot(dot_id).await {
+            error!("Failed to archive ABIs of deleted dot {}: {}", dot_id, e);
+        }
+    }
+}
+
+fn registry_status(error: RegistryError) -> Status {
+    match error {
+        RegistryError::AbiNotFound(_) => Status::not_found(error.to_string()),
+        RegistryError::AbiAlreadyExists(_) => Status::already_exists(error.to_string()),
+        RegistryError::InvalidVersion(_) => Status::invalid_argument(error.to_string()),
+        _ => Status::internal(error.to_string()),
+    }
 }
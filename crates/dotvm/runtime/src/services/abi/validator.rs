@@ -20,7 +20,7 @@ use std::collections::HashMap;
 use thiserror::Error;
 use tracing::{error, info, instrument};
 
-use crate::proto::vm_service::{AbiField, DotAbi, ValidateAbiRequest, ValidateAbiResponse, ValidationError, ValidationWarning};
+use crate::proto::vm_service::{AbiEvent, AbiField, DotAbi, ValidateAbiRequest, ValidateAbiResponse, ValidationError, ValidationWarning};
 
 #[derive(Error, Debug)]
 pub enum ValidatorError {
@@ -63,6 +63,9 @@ impl AbiValidator {
         // Validate paradot dependencies
         self.validate_paradots(&abi.paradots, &mut errors, &mut warnings)?;
 
+        // Validate declared events and their payload fields
+        self.validate_events(&abi.events, &mut errors, &mut warnings)?;
+
         // Validate permissions if present
         if let Some(permissions) = &abi.permissions {
             self.validate_permissions(permissions, &mut errors, &mut warnings)?;
@@ -264,11 +267,43 @@ impl AbiValidator {
         Ok(())
     }
 
+    fn validate_events(&self, events: &[AbiEvent], errors: &mut Vec<ValidationError>, warnings: &mut Vec<ValidationWarning>) -> Result<(), ValidatorError> {
+        let mut declared = std::collections::HashSet::new();
+
+        for event in events {
+            if event.name.is_empty() {
+                errors.push(ValidationError {
+                    field: "events".to_string(),
+                    message: "Event name cannot be empty".to_string(),
+                    error_code: "EMPTY_EVENT_NAME".to_string(),
+                });
+            }
+
+            if event.version == 0 {
+                errors.push(ValidationError {
+                    field: format!("events.{}.version", event.name),
+                    message: "Event versions start at 1".to_string(),
+                    error_code: "INVALID_EVENT_VERSION".to_string(),
+                });
+            } else if !declared.insert((&event.name, event.version)) {
+                errors.push(ValidationError {
+                    field: format!("events.{}.version", event.name),
+                    message: format!("Version {} of the event is declared twice", event.version),
+                    error_code: "DUPLICATE_EVENT".to_string(),
+                });
+            }
+
+            self.validate_fields(&event.fields, &format!("events.{}.field", event.name), errors, warnings)?;
+        }
+
+        Ok(())
+    }
+
     fn validate_permissions(&self, permissions: &crate::proto::vm_service::PermissionConfig, errors: &mut Vec<ValidationError>, warnings: &mut Vec<ValidationWarning>) -> Result<(), ValidatorError> {
         // TODO: Validate permission configuration
         // For now, just basic validation
 
-        if permissions.public_operations.is_empty() && permissions.protected_operations.is_empty() {
+        if permissions.public_operations.is_empty() && permissions.protected_operations.is_empty() && permissions.owner_operations.is_empty() {
             warnings.push(ValidationWarning {
                 field: "permissions".to_string(),
                 message: "No operations defined in permissions".to_string(),
@@ -276,6 +311,22 @@ impl AbiValidator {
             });
         }
 
+        for access in &permissions.collections {
+            if access.collection.is_empty() {
+                errors.push(ValidationError {
+                    field: "permissions.collections".to_string(),
+                    message: "Collection name cannot be empty".to_string(),
+                    error_code: "EMPTY_COLLECTION_NAME".to_string(),
+                });
+            } else if !access.read && !access.write {
+                warnings.push(ValidationWarning {
+                    field: format!("permissions.collections.{}", access.collection),
+                    message: "Collection is declared without read or write access".to_string(),
+                    warning_code: "NO_COLLECTION_ACCESS".to_string(),
+                });
+            }
+        }
+
         Ok(())
     }
 
@@ -0,0 +1,448 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! ABI generation from transpiled WASM modules
+//!
+//! Exported functions become ABI operations typed from their WASM signatures.
+//! Calls to imported `get_state`/`set_state` host functions become state schema
+//! entries when their key is a constant string in a data segment. Exports that cannot be expressed in the ABI are
+//! skipped with a warning rather than failing the whole generation.
+//!
+//! The output is deterministic: operations are ordered by name, state entries by
+//! key, and the proto type attribute maps (which prost encodes in hash order) are
+//! left empty, so the same module always yields byte-identical encodings.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use dotvm_compiler::wasm::{WasmExportKind, WasmFunctionType, WasmImportKind, WasmInstruction, WasmModule, WasmValueType};
+use serde::Serialize;
+
+use crate::proto::vm_service::{AbiField, AbiOperation, AbiType, DotAbi, StateSchemaEntry};
+
+/// ABI version written into generated ABIs
+pub const GENERATED_ABI_VERSION: &str = "1.0.0";
+
+/// Substring identifying host imports that read state
+const GET_STATE_IMPORT: &str = "get_state";
+/// Substring identifying host imports that write state
+const SET_STATE_IMPORT: &str = "set_state";
+
+/// Result of generating an ABI from a WASM module
+#[derive(Debug, Clone)]
+pub struct WasmAbi {
+    pub abi: DotAbi,
+    /// Canonical JSON form of `abi`
+    pub canonical_json: String,
+    /// Exports and state accesses that could not be described
+    pub warnings: Vec<String>,
+}
+
+/// Generate the ABI of `module`, naming the dot `dot_name`
+pub fn generate_wasm_abi(module: &WasmModule, dot_name: &str) -> WasmAbi {
+    let mut warnings = Vec::new();
+    let export_names = function_export_names(module);
+
+    let mut operations = Vec::new();
+    let mut exports: Vec<_> = module.exports.iter().filter(|export| export.kind == WasmExportKind::Function).collect();
+    exports.sort_by(|a, b| a.name.cmp(&b.name));
+    for export in exports {
+        let Some(signature) = function_signature(module, export.index) else {
+            warnings.push(format!("export `{}` refers to unknown function {}", export.name, export.index));
+            continue;
+        };
+        match operation(&export.name, export.index, signature) {
+            Ok(operation) => operations.push(operation),
+            Err(reason) => warnings.push(format!("export `{}` skipped: {}", export.name, reason)),
+        }
+    }
+
+    let state_schema = state_schema(module, &export_names, &mut warnings);
+
+    let abi = DotAbi {
+        dot_name: dot_name.to_string(),
+        version: GENERATED_ABI_VERSION.to_string(),
+        description: format!("Generated from the exports of the {} WASM module", dot_name),
+        inputs: vec![],
+        outputs: vec![],
+        paradots: vec![],
+        ui_hints: None,
+        permissions: None,
+        operations,
+        state_schema,
+        dependencies: vec![],
+        deterministic: false,
+        events: vec![],
+    };
+    let canonical_json = canonical_json(&abi);
+
+    WasmAbi { abi, canonical_json, warnings }
+}
+
+/// Signature of function `index` in the function index space (imports first)
+fn function_signature(module: &WasmModule, index: u32) -> Option<&WasmFunctionType> {
+    let index = index as usize;
+    let imported: Vec<u32> = module
+        .imports
+        .iter()
+        .filter_map(|import| match import.kind {
+            WasmImportKind::Function { type_index } => Some(type_index),
+            _ => None,
+        })
+        .collect();
+
+    let type_index = match imported.get(index) {
+        Some(type_index) => *type_index,
+        None => *module.function_types.get(index - imported.len())?,
+    };
+    module.types.get(type_index as usize)
+}
+
+/// Exported names of functions, keyed by function index
+fn function_export_names(module: &WasmModule) -> HashMap<u32, &str> {
+    let mut names = HashMap::new();
+    for export in module.exports.iter().filter(|export| export.kind == WasmExportKind::Function) {
+        // Keep the smallest name when a function is exported twice, for determinism
+        names.entry(export.index).and_modify(|name: &mut &str| *name = (*name).min(export.name.as_str())).or_insert(export.name.as_str());
+    }
+    names
+}
+
+fn operation(name: &str, function_index: u32, signature: &WasmFunctionType) -> Result<AbiOperation, String> {
+    if signature.results.len() > 1 {
+        return Err(format!("{} return values, the ABI supports at most one", signature.results.len()));
+    }
+
+    let params = signature
+        .params
+        .iter()
+        .enumerate()
+        .map(|(i, value_type)| abi_field(format!("arg{i}"), *value_type))
+        .collect::<Result<Vec<_>, _>>()?;
+    let returns = signature.results.iter().map(|value_type| abi_field("result".to_string(), *value_type)).collect::<Result<Vec<_>, _>>()?;
+
+    Ok(AbiOperation {
+        name: name.to_string(),
+        params,
+        returns,
+        function_index,
+    })
+}
+
+fn abi_field(name: String, value_type: WasmValueType) -> Result<AbiField, String> {
+    match value_type {
+        WasmValueType::I32 | WasmValueType::I64 | WasmValueType::F32 | WasmValueType::F64 => Ok(AbiField {
+            name,
+            field_type: Some(AbiType {
+                type_name: value_type.as_str().to_string(),
+                generic_params: vec![],
+                attributes: HashMap::new(),
+            }),
+            description: String::new(),
+            constraints: None,
+            required: true,
+            default_value: vec![],
+        }),
+        other => Err(format!("{} values have no ABI type", other)),
+    }
+}
+
+/// Whether a state host import reads or writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StateAccess {
+    Read,
+    Write,
+}
+
+/// Imported state host functions with their access kind and arity
+fn state_imports(module: &WasmModule) -> HashMap<u32, (StateAccess, usize)> {
+    let mut imports = HashMap::new();
+    let mut function_index = 0u32;
+    for import in &module.imports {
+        let WasmImportKind::Function { type_index } = import.kind else {
+            continue;
+        };
+        let access = if import.name.contains(GET_STATE_IMPORT) {
+            Some(StateAccess::Read)
+        } else if import.name.contains(SET_STATE_IMPORT) {
+            Some(StateAccess::Write)
+        } else {
+            None
+        };
+        if let (Some(access), Some(signature)) = (access, module.types.get(type_index as usize)) {
+            imports.insert(function_index, (access, signature.params.len()));
+        }
+        function_index += 1;
+    }
+    imports
+}
+
+#[derive(Default)]
+struct StateKeyUsage {
+    read: bool,
+    written: bool,
+    accessed_by: BTreeSet<String>,
+}
+
+fn state_schema(module: &WasmModule, export_names: &HashMap<u32, &str>, warnings: &mut Vec<String>) -> Vec<StateSchemaEntry> {
+    let state_imports = state_imports(module);
+    if state_imports.is_empty() {
+        return vec![];
+    }
+
+    let imported_functions = module.import_function_count() as u32;
+    let mut keys: BTreeMap<String, StateKeyUsage> = BTreeMap::new();
+    for (offset, function) in module.functions.iter().enumerate() {
+        let index = imported_functions + offset as u32;
+        let caller = export_names.get(&index).map(|name| name.to_string()).unwrap_or_else(|| format!("func[{index}]"));
+
+        for (position, instruction) in function.body.iter().enumerate() {
+            let WasmInstruction::Call { function_index } = instruction else {
+                continue;
+            };
+            let Some(&(access, arity)) = state_imports.get(function_index) else {
+                continue;
+            };
+
+            // The key is passed as the first (pointer, length) pair; only constant arguments
+            // pushed right before the call can be resolved statically
+            let key = position
+                .checked_sub(arity)
+                .filter(|_| arity >= 2)
+                .and_then(|start| match &function.body[start..start + 2] {
+                    [WasmInstruction::I32Const { value: ptr }, WasmInstruction::I32Const { value: len }] => constant_string(module, *ptr, *len),
+                    _ => None,
+                });
+            let Some(key) = key else {
+                warnings.push(format!("state access in `{}` uses a key that is not a constant string", caller));
+                continue;
+            };
+
+            let usage = keys.entry(key).or_default();
+            match access {
+                StateAccess::Read => usage.read = true,
+                StateAccess::Write => usage.written = true,
+            }
+            usage.accessed_by.insert(caller.clone());
+        }
+    }
+
+    keys.into_iter()
+        .map(|(key, usage)| StateSchemaEntry {
+            key,
+            read: usage.read,
+            written: usage.written,
+            accessed_by: usage.accessed_by.into_iter().collect(),
+        })
+        .collect()
+}
+
+/// UTF-8 string at `ptr..ptr + len` in an active data segment with a constant offset
+fn constant_string(module: &WasmModule, ptr: i32, len: i32) -> Option<String> {
+    let (ptr, len) = (u32::try_from(ptr).ok()?, u32::try_from(len).ok()?);
+    module.data_segments.iter().find_map(|segment| {
+        let [WasmInstruction::I32Const { value: base }] = segment.offset.as_slice() else {
+            return None;
+        };
+        let start = ptr.checked_sub(u32::try_from(*base).ok()?)? as usize;
+        let bytes = segment.data.get(start..start.checked_add(len as usize)?)?;
+        String::from_utf8(bytes.to_vec()).ok()
+    })
+}
+
+/// Canonical JSON layout; field order here is the order in the output
+#[derive(Serialize)]
+struct CanonicalAbi<'a> {
+    dot_name: &'a str,
+    version: &'a str,
+    operations: Vec<CanonicalOperation<'a>>,
+    state_schema: Vec<CanonicalStateEntry<'a>>,
+}
+
+#[derive(Serialize)]
+struct CanonicalOperation<'a> {
+    name: &'a str,
+    function_index: u32,
+    params: Vec<CanonicalField<'a>>,
+    returns: Vec<CanonicalField<'a>>,
+}
+
+#[derive(Serialize)]
+struct CanonicalField<'a> {
+    name: &'a str,
+    #[serde(rename = "type")]
+    type_name: &'a str,
+}
+
+#[derive(Serialize)]
+struct CanonicalStateEntry<'a> {
+    key: &'a str,
+    read: bool,
+    written: bool,
+    accessed_by: &'a [String],
+}
+
+/// Canonical JSON of the parts of a generated ABI derived from the module
+pub fn canonical_json(abi: &DotAbi) -> String {
+    let fields = |fields: &'_ [AbiField]| -> Vec<CanonicalField<'_>> {
+        fields
+            .iter()
+            .map(|field| CanonicalField {
+                name: &field.name,
+                type_name: field.field_type.as_ref().map_or("", |t| t.type_name.as_str()),
+            })
+            .collect()
+    };
+
+    let canonical = CanonicalAbi {
+        dot_name: &abi.dot_name,
+        version: &abi.version,
+        operations: abi
+            .operations
+            .iter()
+            .map(|operation| CanonicalOperation {
+                name: &operation.name,
+                function_index: operation.function_index,
+                params: fields(&operation.params),
+                returns: fields(&operation.returns),
+            })
+            .collect(),
+        state_schema: abi
+            .state_schema
+            .iter()
+            .map(|entry| CanonicalStateEntry {
+                key: &entry.key,
+                read: entry.read,
+                written: entry.written,
+                accessed_by: &entry.accessed_by,
+            })
+            .collect(),
+    };
+    // Only strings, numbers and booleans are serialized, which cannot fail
+    serde_json::to_string(&canonical).expect("canonical ABI serializes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dotvm_compiler::wasm::{WasmDataSegment, WasmExport, WasmFunction, WasmImport};
+    use prost::Message;
+
+    fn state_module() -> WasmModule {
+        let mut module = WasmModule::new();
+        let i32 = WasmValueType::I32;
+        module.types = vec![
+            WasmFunctionType::new(vec![i32, i32], vec![i32]),
+            WasmFunctionType::new(vec![i32, i32, i32, i32], vec![]),
+            WasmFunctionType::new(vec![i32], vec![i32]),
+            WasmFunctionType::new(vec![], vec![i32, i32]),
+            WasmFunctionType::new(vec![WasmValueType::V128], vec![]),
+        ];
+        module.imports = vec![
+            WasmImport::new("env".to_string(), "get_state".to_string(), WasmImportKind::Function { type_index: 0 }),
+            WasmImport::new("env".to_string(), "set_state".to_string(), WasmImportKind::Function { type_index: 1 }),
+        ];
+        module.data_segments = vec![WasmDataSegment::new(0, vec![WasmInstruction::I32Const { value: 1024 }], b"counterowner".to_vec())];
+
+        // increment(i32) -> i32 reads and writes "counter"
+        let increment = WasmFunction::new(
+            module.types[2].clone(),
+            vec![],
+            vec![
+                WasmInstruction::I32Const { value: 1024 },
+                WasmInstruction::I32Const { value: 7 },
+                WasmInstruction::Call { function_index: 0 },
+                WasmInstruction::Drop,
+                WasmInstruction::I32Const { value: 1024 },
+                WasmInstruction::I32Const { value: 7 },
+                WasmInstruction::LocalGet { local_index: 0 },
+                WasmInstruction::I32Const { value: 4 },
+                WasmInstruction::Call { function_index: 1 },
+                WasmInstruction::LocalGet { local_index: 0 },
+            ],
+        );
+        // owner() -> i32 reads "owner"
+        let owner = WasmFunction::new(
+            module.types[2].clone(),
+            vec![],
+            vec![
+                WasmInstruction::I32Const { value: 1031 },
+                WasmInstruction::I32Const { value: 5 },
+                WasmInstruction::Call { function_index: 0 },
+            ],
+        );
+        let pair = WasmFunction::new(module.types[3].clone(), vec![], vec![]);
+        let simd = WasmFunction::new(module.types[4].clone(), vec![], vec![]);
+        module.function_types = vec![2, 2, 3, 4];
+        module.functions = vec![increment, owner, pair, simd];
+        module.exports = vec![
+            WasmExport::new("owner".to_string(), WasmExportKind::Function, 3),
+            WasmExport::new("increment".to_string(), WasmExportKind::Function, 2),
+            WasmExport::new("pair".to_string(), WasmExportKind::Function, 4),
+            WasmExport::new("simd".to_string(), WasmExportKind::Function, 5),
+        ];
+        module
+    }
+
+    #[test]
+    fn test_exports_become_operations() {
+        let generated = generate_wasm_abi(&state_module(), "counter");
+
+        let names: Vec<_> = generated.abi.operations.iter().map(|op| op.name.as_str()).collect();
+        assert_eq!(names, vec!["increment", "owner"]);
+        let increment = &generated.abi.operations[0];
+        assert_eq!(increment.params.len(), 1);
+        assert_eq!(increment.params[0].field_type.as_ref().unwrap().type_name, "i32");
+        assert_eq!(increment.returns[0].name, "result");
+
+        // Multivalue and SIMD exports are reported, not fatal
+        assert_eq!(generated.warnings.len(), 2);
+        assert!(generated.warnings.iter().any(|w| w.contains("`pair`") && w.contains("2 return values")));
+        assert!(generated.warnings.iter().any(|w| w.contains("`simd`") && w.contains("v128")));
+    }
+
+    #[test]
+    fn test_state_accesses_become_schema_entries() {
+        let generated = generate_wasm_abi(&state_module(), "counter");
+
+        let schema = &generated.abi.state_schema;
+        assert_eq!(schema.len(), 2);
+        assert_eq!(schema[0].key, "counter");
+        assert!(schema[0].read && schema[0].written);
+        assert_eq!(schema[0].accessed_by, vec!["increment"]);
+        assert_eq!(schema[1].key, "owner");
+        assert!(schema[1].read && !schema[1].written);
+    }
+
+    #[test]
+    fn test_dynamic_state_key_warns() {
+        let mut module = state_module();
+        module.functions[1].body[0] = WasmInstruction::LocalGet { local_index: 0 };
+
+        let generated = generate_wasm_abi(&module, "counter");
+        assert_eq!(generated.abi.state_schema.len(), 1);
+        assert!(generated.warnings.iter().any(|w| w.contains("`owner`") && w.contains("not a constant string")));
+    }
+
+    #[test]
+    fn test_output_is_deterministic() {
+        let first = generate_wasm_abi(&state_module(), "counter");
+        let second = generate_wasm_abi(&state_module(), "counter");
+
+        assert_eq!(first.canonical_json, second.canonical_json);
+        assert_eq!(first.abi.encode_to_vec(), second.abi.encode_to_vec());
+        assert!(first.canonical_json.starts_with(r#"{"dot_name":"counter","version":"1.0.0","operations":[{"name":"increment","function_index":2,"params":[{"name":"arg0","type":"i32"}]"#));
+    }
+}
@@ -14,15 +14,22 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use dotdb_core::storage_engine::{StorageConfigDelta, StorageEngine, StorageError};
+use dotvm_common::telemetry::REQUEST_ID_HEADER;
+use dotvm_runtime::rollback::lib::{RollbackError, RollbackResult, SystemState};
+use dotvm_runtime::rollback::verification::DefaultConsistencyVerifier;
+use dotvm_runtime::rollback::{CheckpointCategory, CheckpointTarget, ConsistencyVerifier, OperationCheckpoints, VerificationResult};
 use futures::Stream;
 use std::{
     collections::HashMap,
     pin::Pin,
     sync::Arc,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use tokio::sync::RwLock;
-use tonic::{Request, Response, Result as TonicResult, Status};
+use tonic::{Request, Response, Result as TonicResult, Status, Streaming};
+
+use super::replication::ReplicationRole;
 
 // Import proto from the main crate to avoid duplicate compilation
 use crate::proto::database_service as proto;
@@ -34,6 +41,12 @@ pub struct DatabaseServiceImpl {
     // In-memory storage for demonstration
     // In production, this would be connected to actual database backend
     collections: Arc<RwLock<HashMap<String, Collection>>>,
+    /// Storage engine reconfigured by `UpdateStorageConfig`, if the node runs one
+    storage: Option<Arc<StorageEngine>>,
+    /// Whether this node's documents are replicated from or to other nodes
+    replication: Option<ReplicationRole>,
+    /// Where storage config changes are checkpointed before they are applied
+    checkpoints: Option<Arc<OperationCheckpoints>>,
 }
 
 #[derive(Debug, Clone)]
@@ -55,6 +68,54 @@ impl DatabaseServiceImpl {
     pub fn new() -> Self {
         Self {
             collections: Arc::new(RwLock::new(HashMap::new())),
+            storage: None,
+            replication: None,
+            checkpoints: None,
+        }
+    }
+
+    /// Serve storage admin calls against `storage`
+    pub fn with_storage(mut self, storage: Arc<StorageEngine>) -> Self {
+        self.storage = Some(storage);
+        self.register_checkpoint_target();
+        self
+    }
+
+    /// Checkpoint the storage settings before each config change, rolling back changes that fail verification
+    pub fn with_checkpoints(mut self, checkpoints: Arc<OperationCheckpoints>) -> Self {
+        self.checkpoints = Some(checkpoints);
+        self.register_checkpoint_target();
+        self
+    }
+
+    fn register_checkpoint_target(&self) {
+        if let (Some(storage), Some(checkpoints)) = (&self.storage, &self.checkpoints) {
+            checkpoints.register_target(CheckpointCategory::ConfigMigration, Arc::new(StorageConfigTarget { storage: storage.clone() }));
+        }
+    }
+
+    /// Serve replication calls and refuse writes on a replica
+    pub fn with_replication(mut self, replication: ReplicationRole) -> Self {
+        self.replication = Some(replication);
+        self
+    }
+
+    /// Writes go to the primary; a replica refuses them with its primary's address
+    fn check_writable(&self) -> Result<(), Status> {
+        match &self.replication {
+            Some(ReplicationRole::Replica(follower)) => Err(follower.read_only_status()),
+            _ => Ok(()),
+        }
+    }
+
+    fn replication_primary(&self) -> Result<&Arc<super::replication::ReplicationPrimary>, Status> {
+        match &self.replication {
+            Some(ReplicationRole::Primary(primary)) => Ok(primary),
+            Some(ReplicationRole::Replica(follower)) => Err(Status::failed_precondition(format!(
+                "This node is a replica of {}; replicate from the primary",
+                follower.replica().primary_address()
+            ))),
+            None => Err(Status::unavailable("This node does not replicate its documents")),
         }
     }
 
@@ -116,6 +177,7 @@ impl DatabaseService for DatabaseServiceImpl {
     }
 
     async fn put(&self, request: Request<PutRequest>) -> TonicResult<Response<PutResponse>> {
+        self.check_writable()?;
         let req = request.into_inner();
 
         self.ensure_collection_exists(&req.collection).await.map_err(|e| Status::internal(e))?;
@@ -141,6 +203,7 @@ impl DatabaseService for DatabaseServiceImpl {
     }
 
     async fn delete(&self, request: Request<DeleteRequest>) -> TonicResult<Response<DeleteResponse>> {
+        self.check_writable()?;
         let req = request.into_inner();
 
         let mut collections = self.collections.write().await;
@@ -169,6 +232,10 @@ impl DatabaseService for DatabaseServiceImpl {
 
     async fn batch_operation(&self, request: Request<BatchOperationRequest>) -> TonicResult<Response<BatchOperationResponse>> {
         let req = request.into_inner();
+        // Batches of GETs are reads
+        if req.operations.iter().any(|operation| operation.r#type != 0) {
+            self.check_writable()?;
+        }
 
         self.ensure_collection_exists(&req.collection).await.map_err(|e| Status::internal(e))?;
 
@@ -239,7 +306,48 @@ impl DatabaseService for DatabaseServiceImpl {
         Ok(Response::new(response))
     }
 
+    async fn apply_write_batch(&self, request: Request<WriteBatchRequest>) -> TonicResult<Response<WriteBatchResponse>> {
+        self.check_writable()?;
+        let req = request.into_inner();
+
+        // Checked and applied under one lock, so other calls see all of the batch or none of it
+        let mut collections = self.collections.write().await;
+        if let Err((index, error_message)) = check_write_batch(&collections, &req.writes) {
+            tracing::debug!(request_id = %req.request_id, index, %error_message, "Write batch refused");
+            return Ok(Response::new(WriteBatchResponse {
+                success: false,
+                applied: 0,
+                failed_index: index as u32,
+                error_message,
+            }));
+        }
+
+        let applied = req.writes.len() as u32;
+        for write in req.writes {
+            let collection = collections.entry(write.collection.clone()).or_insert_with(|| Collection {
+                name: write.collection.clone(),
+                config: None,
+                data: HashMap::new(),
+                indices: HashMap::new(),
+            });
+            if write.delete {
+                collection.data.remove(&write.key);
+            } else {
+                collection.data.insert(write.key, write.value);
+            }
+        }
+
+        tracing::debug!(request_id = %req.request_id, applied, "Write batch applied");
+        Ok(Response::new(WriteBatchResponse {
+            success: true,
+            applied,
+            failed_index: 0,
+            error_message: String::new(),
+        }))
+    }
+
     async fn create_collection(&self, request: Request<CreateCollectionRequest>) -> TonicResult<Response<CreateCollectionResponse>> {
+        self.check_writable()?;
         let req = request.into_inner();
 
         let mut collections = self.collections.write().await;
@@ -294,6 +402,7 @@ impl DatabaseService for DatabaseServiceImpl {
     }
 
     async fn drop_collection(&self, request: Request<DropCollectionRequest>) -> TonicResult<Response<DropCollectionResponse>> {
+        self.check_writable()?;
         let req = request.into_inner();
 
         let mut collections = self.collections.write().await;
@@ -358,6 +467,7 @@ impl DatabaseService for DatabaseServiceImpl {
     }
 
     async fn create_index(&self, request: Request<CreateIndexRequest>) -> TonicResult<Response<CreateIndexResponse>> {
+        self.check_writable()?;
         let req = request.into_inner();
 
         let response = CreateIndexResponse {
@@ -373,6 +483,7 @@ impl DatabaseService for DatabaseServiceImpl {
     }
 
     async fn drop_index(&self, request: Request<DropIndexRequest>) -> TonicResult<Response<DropIndexResponse>> {
+        self.check_writable()?;
         let response = DropIndexResponse {
             success: false,
             error_message: "DropIndex not yet implemented".to_string(),
@@ -390,6 +501,7 @@ impl DatabaseService for DatabaseServiceImpl {
     }
 
     async fn commit_transaction(&self, request: Request<CommitTransactionRequest>) -> TonicResult<Response<CommitTransactionResponse>> {
+        self.check_writable()?;
         let response = CommitTransactionResponse {
             success: false,
             error_message: "Transactions not yet implemented".to_string(),
@@ -405,6 +517,93 @@ impl DatabaseService for DatabaseServiceImpl {
         Ok(Response::new(response))
     }
 
+    async fn update_storage_config(&self, request: Request<UpdateStorageConfigRequest>) -> TonicResult<Response<UpdateStorageConfigResponse>> {
+        let operation_id = request
+            .metadata()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let req = request.into_inner();
+        let storage = self.storage.clone().ok_or_else(|| Status::unavailable("This node has no storage engine to reconfigure"))?;
+
+        let mut delta = StorageConfigDelta::default();
+        for (key, value) in &req.settings {
+            delta.set(key, value).map_err(storage_status)?;
+        }
+
+        // Restarting the flusher joins its thread, so keep it off the async workers
+        let checkpoints = self.checkpoints.clone();
+        let (changes, checkpoint_id) = tokio::task::spawn_blocking(move || {
+            let checkpoint_id = match &checkpoints {
+                Some(checkpoints) => Some(
+                    checkpoints
+                        .create(CheckpointCategory::ConfigMigration, &operation_id, STORAGE_SUBJECT)
+                        .map_err(|e| Status::internal(format!("Failed to checkpoint storage config: {e}")))?,
+                ),
+                None => None,
+            };
+            let changes = storage.apply_config_delta(&delta).map_err(storage_status)?;
+            if let (Some(checkpoints), Some(checkpoint_id)) = (&checkpoints, &checkpoint_id) {
+                checkpoints.verify(checkpoint_id, &storage_config_verifier()).map_err(|e| match e {
+                    RollbackError::VerificationFailed(reason) => Status::aborted(format!("Storage config change failed verification and was rolled back to checkpoint {checkpoint_id}: {reason}")),
+                    e => Status::internal(format!("Failed to verify storage config change: {e}")),
+                })?;
+            }
+            Ok::<_, Status>((changes, checkpoint_id))
+        })
+        .await
+        .map_err(|e| Status::internal(format!("Storage config update failed: {e}")))??;
+
+        let response = UpdateStorageConfigResponse {
+            checkpoint_id: checkpoint_id.unwrap_or_default(),
+            changes: changes
+                .into_iter()
+                .map(|change| StorageConfigChange {
+                    setting: change.setting.to_string(),
+                    old_value: change.old_value,
+                    new_value: change.new_value,
+                })
+                .collect(),
+        };
+        Ok(Response::new(response))
+    }
+
+    type ReplicateChangesStream = Pin<Box<dyn Stream<Item = Result<ChangeBatch, Status>> + Send>>;
+
+    async fn replicate_changes(&self, request: Request<Streaming<ReplicaProgress>>) -> TonicResult<Response<Self::ReplicateChangesStream>> {
+        let primary = self.replication_primary()?;
+        Ok(Response::new(Box::pin(primary.stream_changes(request.into_inner()))))
+    }
+
+    type GetSnapshotStream = Pin<Box<dyn Stream<Item = Result<SnapshotChunk, Status>> + Send>>;
+
+    async fn get_snapshot(&self, request: Request<GetSnapshotRequest>) -> TonicResult<Response<Self::GetSnapshotStream>> {
+        let chunks = self.replication_primary()?.snapshot_chunks().await?;
+        Ok(Response::new(Box::pin(futures::stream::iter(chunks.into_iter().map(Ok)))))
+    }
+
+    async fn get_replication_status(&self, request: Request<GetReplicationStatusRequest>) -> TonicResult<Response<GetReplicationStatusResponse>> {
+        let req = request.into_inner();
+        let response = match &self.replication {
+            Some(ReplicationRole::Primary(primary)) => {
+                primary.check_sequence(req.min_sequence)?;
+                primary.status()
+            }
+            Some(ReplicationRole::Replica(follower)) => {
+                if req.min_sequence > 0 {
+                    follower.wait_for_sequence(req.min_sequence, Duration::from_millis(req.wait_timeout_ms.into())).await?;
+                }
+                follower.status()
+            }
+            None => GetReplicationStatusResponse {
+                role: "standalone".to_string(),
+                ..Default::default()
+            },
+        };
+        Ok(Response::new(response))
+    }
+
     async fn get_database_status(&self, request: Request<GetDatabaseStatusRequest>) -> TonicResult<Response<GetDatabaseStatusResponse>> {
         let collections_count = self.collections.read().await.len();
 
@@ -479,3 +678,156 @@ impl DatabaseService for DatabaseServiceImpl {
         Ok(Response::new(response))
     }
 }
+
+/// Subject of storage config checkpoints, since a node runs a single engine
+const STORAGE_SUBJECT: &str = "storage";
+
+/// Settings a running engine can change, captured before a config change
+const LIVE_SETTINGS: [&str; 4] = ["flush_interval_ms", "max_dirty_pages", "compaction_io_budget_bytes_per_sec", "buffer_pool_size"];
+
+/// Captures and restores the live settings of the storage engine
+///
+/// The buffer pool cannot shrink while running, so restoring leaves a grown pool at its new size.
+struct StorageConfigTarget {
+    storage: Arc<StorageEngine>,
+}
+
+impl CheckpointTarget for StorageConfigTarget {
+    fn capture(&self, _subject: &str) -> RollbackResult<SystemState> {
+        let config = self.storage.config();
+        let values = [
+            config.flush_interval_ms.to_string(),
+            config.max_dirty_pages.to_string(),
+            config.compaction_io_budget_bytes_per_sec.to_string(),
+            config.buffer_pool_size.to_string(),
+        ];
+        Ok(LIVE_SETTINGS.iter().zip(values).map(|(setting, value)| (setting.to_string(), value.into_bytes())).collect())
+    }
+
+    fn restore(&self, _subject: &str, state: &SystemState) -> RollbackResult<()> {
+        let mut delta = StorageConfigDelta::default();
+        for (setting, value) in state.iter().filter(|(setting, _)| setting.as_str() != "buffer_pool_size") {
+            delta.set(setting, &String::from_utf8_lossy(value)).map_err(|e| RollbackError::RollbackFailed(e.to_string()))?;
+        }
+        self.storage.apply_config_delta(&delta).map_err(|e| RollbackError::RollbackFailed(e.to_string()))?;
+        Ok(())
+    }
+}
+
+/// Checks a storage config change must pass, or it is rolled back
+fn storage_config_verifier() -> DefaultConsistencyVerifier {
+    let mut verifier = DefaultConsistencyVerifier::new();
+    verifier
+        .add_verification_check(
+            "dirty_pages_fit_buffer_pool",
+            |state| {
+                let setting = |name: &str| state.get(name).and_then(|value| String::from_utf8_lossy(value).parse::<usize>().ok());
+                match (setting("max_dirty_pages"), setting("buffer_pool_size")) {
+                    (Some(dirty), Some(pool)) if dirty > pool => {
+                        VerificationResult::Invalid(format!("max_dirty_pages ({dirty}) exceeds the buffer pool ({pool} pages), so dirty pages could never all be held"))
+                    }
+                    _ => VerificationResult::Valid,
+                }
+            },
+            true,
+        )
+        .expect("a fresh verifier accepts checks");
+    verifier
+}
+
+fn storage_status(error: StorageError) -> Status {
+    match error {
+        StorageError::RestartRequired { .. } => Status::failed_precondition(error.to_string()),
+        StorageError::InvalidConfig { .. } => Status::invalid_argument(error.to_string()),
+        _ => Status::internal(error.to_string()),
+    }
+}
+
+/// Position and reason of the first write in `writes` that cannot be applied, each write
+/// seeing the ones before it
+fn check_write_batch(collections: &HashMap<String, Collection>, writes: &[StateWrite]) -> Result<(), (usize, String)> {
+    // Whether each key touched so far exists once the writes before the current one are applied
+    let mut exists: HashMap<(&str, &str), bool> = HashMap::new();
+    for (index, write) in writes.iter().enumerate() {
+        if write.collection.is_empty() || write.key.is_empty() {
+            return Err((index, "State writes need a collection and a key".to_string()));
+        }
+
+        let slot = (write.collection.as_str(), write.key.as_str());
+        if write.delete {
+            let present = exists
+                .get(&slot)
+                .copied()
+                .unwrap_or_else(|| collections.get(&write.collection).is_some_and(|collection| collection.data.contains_key(&write.key)));
+            if !present {
+                return Err((index, format!("Key '{}' not found in collection '{}'", write.key, write.collection)));
+            }
+        }
+        exists.insert(slot, !write.delete);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(key: &str, value: &str) -> StateWrite {
+        StateWrite {
+            collection: "counters".to_string(),
+            key: key.to_string(),
+            value: value.as_bytes().to_vec(),
+            delete: false,
+        }
+    }
+
+    fn delete(key: &str) -> StateWrite {
+        StateWrite {
+            collection: "counters".to_string(),
+            key: key.to_string(),
+            value: vec![],
+            delete: true,
+        }
+    }
+
+    async fn get(service: &DatabaseServiceImpl, key: &str) -> Option<Vec<u8>> {
+        let request = GetRequest {
+            collection: "counters".to_string(),
+            key: key.to_string(),
+            transaction_id: String::new(),
+        };
+        let response = service.get(Request::new(request)).await.unwrap().into_inner();
+        response.success.then_some(response.value)
+    }
+
+    #[tokio::test]
+    async fn test_write_batch_applies_in_order() {
+        let service = DatabaseServiceImpl::new();
+        let batch = WriteBatchRequest {
+            writes: vec![put("a", "1"), put("b", "1"), delete("a"), put("b", "2")],
+            request_id: "req-1".to_string(),
+        };
+
+        let response = service.apply_write_batch(Request::new(batch)).await.unwrap().into_inner();
+        assert!(response.success);
+        assert_eq!(response.applied, 4);
+        assert_eq!(get(&service, "a").await, None);
+        assert_eq!(get(&service, "b").await, Some(b"2".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_refused_write_batch_applies_nothing() {
+        let service = DatabaseServiceImpl::new();
+        let batch = WriteBatchRequest {
+            // The second delete of `a` follows the first, so it has nothing left to remove
+            writes: vec![put("a", "1"), put("b", "1"), delete("a"), delete("a")],
+            request_id: "req-1".to_string(),
+        };
+
+        let response = service.apply_write_batch(Request::new(batch)).await.unwrap().into_inner();
+        assert!(!response.success);
+        assert_eq!((response.applied, response.failed_index), (0, 3));
+        assert_eq!(response.error_message, "Key 'a' not found in collection 'counters'");
+        assert_eq!(get(&service, "b").await, None);
+    }
+}
@@ -0,0 +1,131 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Architectures dots are deployed for and executed on
+//!
+//! A dot's architecture is the one in the header of its bytecode; source the runtime
+//! compiles itself targets the architecture the deployment asks for, or the configured
+//! default. Each execution picks the executor from the supported architectures: the dot's
+//! own, or else the narrowest wider one, which runs it in compatibility mode. A dot no
+//! supported architecture can run is rejected when it deploys.
+
+use dotvm_core::bytecode::{BytecodeHeader, VmArchitecture};
+use dotvm_core::vm::architecture_detector::{ArchitectureDetector, DetectedArch};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ArchitectureError {
+    #[error("Invalid target architecture: {0}")]
+    InvalidTarget(String),
+    #[error("Dot targets {required}, but this runtime executes at most {widest}")]
+    Unsupported { required: VmArchitecture, widest: VmArchitecture },
+}
+
+/// Which architectures the runtime deploys and executes dots for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchitectureConfig {
+    /// Architecture source is compiled for when the deployment names none
+    pub default: VmArchitecture,
+    /// Architectures the runtime has executors for
+    pub supported: Vec<VmArchitecture>,
+}
+
+impl Default for ArchitectureConfig {
+    fn default() -> Self {
+        Self {
+            default: VmArchitecture::Arch64,
+            supported: vec![VmArchitecture::Arch64, VmArchitecture::Arch128],
+        }
+    }
+}
+
+impl ArchitectureConfig {
+    /// Architecture named in the header of `bytecode`, if it starts with one
+    pub fn detect(bytecode: &[u8]) -> Option<VmArchitecture> {
+        BytecodeHeader::from_bytes(bytecode).ok().map(|header| header.architecture)
+    }
+
+    /// Architecture a deployment asks its source to be compiled for; empty asks for the default
+    pub fn target(&self, requested: &str) -> Result<VmArchitecture, ArchitectureError> {
+        if requested.trim().is_empty() {
+            return Ok(self.default);
+        }
+        requested.parse().map_err(ArchitectureError::InvalidTarget)
+    }
+
+    pub fn is_supported(&self, architecture: VmArchitecture) -> bool {
+        self.supported.contains(&architecture)
+    }
+
+    /// Widest architecture the runtime executes
+    pub fn widest(&self) -> VmArchitecture {
+        self.supported.iter().copied().max().unwrap_or(self.default)
+    }
+
+    /// Executor architecture for a dot built for `required`
+    pub fn select(&self, required: VmArchitecture) -> Result<DetectedArch, ArchitectureError> {
+        let detector = ArchitectureDetector::new();
+        let execution = if self.is_supported(required) {
+            Some(required)
+        } else {
+            self.supported.iter().copied().filter(|&architecture| detector.is_compatible(required, architecture)).min()
+        };
+
+        match execution {
+            Some(execution) => Ok(DetectedArch {
+                required,
+                execution,
+                compatibility_mode: execution != required,
+            }),
+            None => Err(ArchitectureError::Unsupported { required, widest: self.widest() }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_prefers_the_dots_own_architecture() {
+        let config = ArchitectureConfig {
+            default: VmArchitecture::Arch64,
+            supported: vec![VmArchitecture::Arch64, VmArchitecture::Arch256, VmArchitecture::Arch512],
+        };
+
+        let native = config.select(VmArchitecture::Arch64).unwrap();
+        assert_eq!((native.execution, native.compatibility_mode), (VmArchitecture::Arch64, false));
+        // Narrower bytecode runs on the narrowest architecture wide enough for it
+        let widened = config.select(VmArchitecture::Arch128).unwrap();
+        assert_eq!((widened.execution, widened.compatibility_mode), (VmArchitecture::Arch256, true));
+
+        let narrow = ArchitectureConfig::default();
+        let error = narrow.select(VmArchitecture::Arch512).unwrap_err();
+        assert_eq!(error.to_string(), "Dot targets arch512, but this runtime executes at most arch128");
+    }
+
+    #[test]
+    fn test_target_and_detection() {
+        let config = ArchitectureConfig::default();
+        assert_eq!(config.target(""), Ok(VmArchitecture::Arch64));
+        assert_eq!(config.target("arch128"), Ok(VmArchitecture::Arch128));
+        assert!(matches!(config.target("WASM"), Err(ArchitectureError::InvalidTarget(_))));
+
+        let bytecode = [BytecodeHeader::new(VmArchitecture::Arch256).to_bytes().as_slice(), &[0x01, 0x02]].concat();
+        assert_eq!(ArchitectureConfig::detect(&bytecode), Some(VmArchitecture::Arch256));
+        assert_eq!(ArchitectureConfig::detect(b"dot counter {}"), None);
+    }
+}
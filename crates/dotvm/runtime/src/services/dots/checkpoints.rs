@@ -0,0 +1,149 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Checkpoints of a dot taken before it is redeployed
+//!
+//! A checkpoint holds which versions the registry had and routed to, the
+//! version of the dot's state and its quota. Restoring one drops versions
+//! deployed since, re-activates the version that was active and makes the
+//! state of the time current again as a new version.
+
+use dotvm_core::vm::execution_controller::QuotaConfig;
+use dotvm_core::vm::execution_controller::resource_allocation::ResourceAllocator;
+use dotvm_runtime::rollback::lib::{RollbackError, RollbackResult, SystemState};
+use dotvm_runtime::rollback::verification::DefaultConsistencyVerifier;
+use dotvm_runtime::rollback::{CheckpointTarget, ConsistencyVerifier, VerificationResult};
+use std::sync::Arc;
+
+use super::executor::DotExecutor;
+use super::registry::{DotRegistry, VersionSnapshot};
+
+const VERSIONS_KEY: &str = "registry.versions";
+const ACTIVE_VERSION_KEY: &str = "registry.active_version";
+const PREVIOUS_VERSION_KEY: &str = "registry.previous_version";
+const STATE_VERSION_KEY: &str = "state.version";
+const QUOTA_MEMORY_KEY: &str = "quota.max_memory_mb";
+const QUOTA_CPU_KEY: &str = "quota.max_cpu_cores";
+const QUOTA_TASKS_KEY: &str = "quota.max_concurrent_tasks";
+
+/// Captures and restores dots around redeploys
+pub struct DotCheckpointTarget {
+    registry: Arc<DotRegistry>,
+    executor: Arc<DotExecutor>,
+    resource_allocator: Arc<ResourceAllocator>,
+}
+
+impl DotCheckpointTarget {
+    pub fn new(registry: Arc<DotRegistry>, executor: Arc<DotExecutor>, resource_allocator: Arc<ResourceAllocator>) -> Self {
+        Self {
+            registry,
+            executor,
+            resource_allocator,
+        }
+    }
+}
+
+impl CheckpointTarget for DotCheckpointTarget {
+    fn capture(&self, dot_id: &str) -> RollbackResult<SystemState> {
+        let snapshot = self.registry.version_snapshot(dot_id).map_err(|e| RollbackError::StateStorageError(e.to_string()))?;
+
+        let mut state = SystemState::new();
+        state.insert(VERSIONS_KEY.to_string(), snapshot.versions.iter().flat_map(|version| version.to_le_bytes()).collect());
+        if let Some(version) = snapshot.active_version {
+            state.insert(ACTIVE_VERSION_KEY.to_string(), version.to_le_bytes().to_vec());
+        }
+        if let Some(version) = snapshot.previous_version {
+            state.insert(PREVIOUS_VERSION_KEY.to_string(), version.to_le_bytes().to_vec());
+        }
+
+        // A dot deployed before its state was set up has no state to capture
+        if let Ok(version) = self.executor.state_store().current_version(dot_id) {
+            state.insert(STATE_VERSION_KEY.to_string(), version.to_le_bytes().to_vec());
+        }
+
+        if let Some(quota) = self.resource_allocator.quota(dot_id) {
+            state.insert(QUOTA_MEMORY_KEY.to_string(), (quota.config.max_memory_mb as u64).to_le_bytes().to_vec());
+            state.insert(QUOTA_CPU_KEY.to_string(), quota.config.max_cpu_cores.to_le_bytes().to_vec());
+            state.insert(QUOTA_TASKS_KEY.to_string(), (quota.config.max_concurrent_tasks as u64).to_le_bytes().to_vec());
+        }
+
+        Ok(state)
+    }
+
+    fn restore(&self, dot_id: &str, state: &SystemState) -> RollbackResult<()> {
+        let snapshot = VersionSnapshot {
+            versions: versions(state),
+            active_version: active_version(state),
+            previous_version: state.get(PREVIOUS_VERSION_KEY).and_then(|bytes| decode_u32(bytes)),
+        };
+        self.registry.restore_versions(dot_id, &snapshot).map_err(|e| RollbackError::RollbackFailed(e.to_string()))?;
+
+        if let Some(version) = state.get(STATE_VERSION_KEY).and_then(|bytes| decode_u64(bytes)) {
+            self.executor.state_store().restore_version(dot_id, version).map_err(|e| RollbackError::RollbackFailed(e.to_string()))?;
+        }
+
+        let quota = (state.get(QUOTA_MEMORY_KEY), state.get(QUOTA_CPU_KEY), state.get(QUOTA_TASKS_KEY));
+        if let (Some(memory), Some(cpu), Some(tasks)) = quota {
+            let cpu = cpu.as_slice().try_into().map(f32::from_le_bytes).unwrap_or_default();
+            self.resource_allocator.set_quota(
+                dot_id,
+                QuotaConfig {
+                    max_memory_mb: decode_u64(memory).unwrap_or_default() as usize,
+                    max_cpu_cores: cpu,
+                    max_concurrent_tasks: decode_u64(tasks).unwrap_or_default() as usize,
+                },
+            );
+        }
+
+        // Memoized results may come from a dropped version
+        self.executor.result_cache().invalidate_dot(dot_id);
+        Ok(())
+    }
+}
+
+/// Checks every redeploy has to pass before it is kept
+pub fn redeploy_verifier() -> DefaultConsistencyVerifier {
+    let mut verifier = DefaultConsistencyVerifier::new();
+    verifier
+        .add_verification_check(
+            "active_version_deployed",
+            |state| match active_version(state) {
+                Some(active) if !versions(state).contains(&active) => VerificationResult::Invalid(format!("active version {active} is not deployed")),
+                _ => VerificationResult::Valid,
+            },
+            true,
+        )
+        .expect("a fresh verifier accepts checks");
+    verifier
+}
+
+/// Version taking executions in a captured dot state
+pub fn active_version(state: &SystemState) -> Option<u32> {
+    state.get(ACTIVE_VERSION_KEY).and_then(|bytes| decode_u32(bytes))
+}
+
+/// Versions deployed in a captured dot state
+pub fn versions(state: &SystemState) -> Vec<u32> {
+    state.get(VERSIONS_KEY).map(|bytes| bytes.chunks_exact(4).filter_map(decode_u32).collect()).unwrap_or_default()
+}
+
+fn decode_u32(bytes: &[u8]) -> Option<u32> {
+    bytes.try_into().ok().map(u32::from_le_bytes)
+}
+
+fn decode_u64(bytes: &[u8]) -> Option<u64> {
+    bytes.try_into().ok().map(u64::from_le_bytes)
+}
@@ -0,0 +1,652 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Live dot debugging
+//!
+//! A debug session runs a deployed dot in the VM's instrumented mode on a blocking
+//! thread. The instruction hook pauses the VM at entry, at breakpoints and after single
+//! steps, and while paused serves inspection requests from the session's command channel.
+//! A dot can only have one debugger attached at a time.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use dotvm_core::bytecode::BytecodeFile;
+use dotvm_core::vm::executor::{ExecutionContext, HookAction, Instruction, InstructionHook, VmExecutor};
+use dotvm_core::vm::stack::StackValue;
+use futures::StreamExt;
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tonic::{Status, Streaming};
+use tracing::{info, warn};
+
+use crate::proto::vm_service::{
+    BreakpointSet, DebugCommand, DebugCommandType, DebugError, DebugEvent, DebugEventType, DebugRequest, DebugResponse, DebugSessionStarted, DebugSessionStopped, ExecutionState,
+    InspectVariable, SetBreakpoint, StackFrame, StartDebugSession, VariableInspection, debug_request, debug_response,
+};
+
+use super::registry::DotRegistry;
+
+/// Name of the function execution starts in. Bytecode carries no function table yet, so
+/// it is the only function breakpoints can be set relative to.
+pub const ENTRY_FUNCTION: &str = "main";
+
+type ResponseSender = UnboundedSender<Result<DebugResponse, Status>>;
+
+/// Commands from the debug stream to the paused or running VM
+#[derive(Debug)]
+enum Control {
+    SetBreakpoint { address: u64, enabled: bool },
+    Continue,
+    Step,
+    Pause,
+    Inspect(InspectVariable),
+    Stop,
+}
+
+/// Tracks which debug session each dot is attached to
+#[derive(Default)]
+pub struct DotDebugger {
+    attached: Mutex<HashMap<String, String>>,
+}
+
+impl DotDebugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attach `session_id` to `dot_id`, returning the session already attached if any
+    fn attach(&self, dot_id: &str, session_id: &str) -> Result<(), String> {
+        let mut attached = self.attached.lock().unwrap();
+        if let Some(existing) = attached.get(dot_id) {
+            return Err(existing.clone());
+        }
+        attached.insert(dot_id.to_string(), session_id.to_string());
+        Ok(())
+    }
+
+    fn detach(&self, dot_id: &str) {
+        self.attached.lock().unwrap().remove(dot_id);
+    }
+
+    /// Serve one debug stream until the client stops the session or disconnects
+    pub fn serve(self: &Arc<Self>, registry: Arc<DotRegistry>, mut requests: Streaming<DebugRequest>) -> UnboundedReceiverStream<Result<DebugResponse, Status>> {
+        let (tx, rx) = unbounded_channel();
+        let debugger = self.clone();
+
+        tokio::spawn(async move {
+            let mut session: Option<Session> = None;
+
+            while let Some(Ok(request)) = requests.next().await {
+                let response = match request.request_type {
+                    Some(debug_request::RequestType::Start(start)) => {
+                        if let Some(active) = session.as_ref() {
+                            Some(debug_error(&active.session_id, "SESSION_ACTIVE", "This stream already has an active debug session"))
+                        } else {
+                            match debugger.start(&registry, start, &tx).await {
+                                Ok((started, active)) => {
+                                    session = Some(active);
+                                    Some(started)
+                                }
+                                Err(error) => Some(error),
+                            }
+                        }
+                    }
+                    Some(debug_request::RequestType::Breakpoint(breakpoint)) => Some(match session.as_mut() {
+                        Some(active) => active.set_breakpoint(breakpoint),
+                        None => debug_error(&breakpoint.session_id, "NO_SESSION", "Start a debug session before setting breakpoints"),
+                    }),
+                    Some(debug_request::RequestType::Command(command)) => match session.as_ref() {
+                        Some(active) => active.command(command),
+                        None => Some(debug_error(&command.session_id, "NO_SESSION", "Start a debug session before sending commands")),
+                    },
+                    Some(debug_request::RequestType::Inspect(inspect)) => match session.as_ref() {
+                        // Answered by the VM thread, which owns the paused state
+                        Some(active) => active.send(Control::Inspect(inspect)).err(),
+                        None => Some(debug_error(&inspect.session_id, "NO_SESSION", "Start a debug session before inspecting state")),
+                    },
+                    Some(debug_request::RequestType::Stop(stop)) => {
+                        if let Some(active) = session.as_ref() {
+                            let _ = active.commands.send(Control::Stop);
+                        }
+                        let _ = tx.send(Ok(DebugResponse {
+                            response_type: Some(debug_response::ResponseType::Stopped(DebugSessionStopped {
+                                session_id: stop.session_id,
+                                reason: "Stopped by client".to_string(),
+                            })),
+                        }));
+                        break;
+                    }
+                    None => None,
+                };
+
+                if let Some(response) = response
+                    && tx.send(Ok(response)).is_err()
+                {
+                    break;
+                }
+            }
+
+            // Dropping the command channel halts a VM still waiting for commands
+            if let Some(active) = session {
+                info!("Debug session {} for dot {} ended", active.session_id, active.dot_id);
+                debugger.detach(&active.dot_id);
+            }
+        });
+
+        UnboundedReceiverStream::new(rx)
+    }
+
+    /// Load the dot and start its instrumented execution, paused before the first instruction
+    async fn start(&self, registry: &DotRegistry, start: StartDebugSession, tx: &ResponseSender) -> Result<(DebugResponse, Session), DebugResponse> {
+        let session_id = start.session_id;
+        if start.attach_to_running {
+            return Err(debug_error(
+                &session_id,
+                "UNSUPPORTED",
+                "Attaching to a running execution is not supported; start a new debug execution instead",
+            ));
+        }
+
+        if let Err(existing) = self.attach(&start.dot_id, &session_id) {
+            return Err(debug_error(
+                &session_id,
+                "ALREADY_ATTACHED",
+                &format!("Dot '{}' already has a debugger attached (session '{existing}')", start.dot_id),
+            ));
+        }
+
+        let bytecode = match registry.get_dot(&start.dot_id).await {
+            Ok(dot) => BytecodeFile::load_from_bytes(&dot.bytecode).map_err(|e| debug_error(&session_id, "INVALID_BYTECODE", &e.to_string())),
+            Err(e) => Err(debug_error(&session_id, "NOT_FOUND", &format!("Dot not found: {e}"))),
+        };
+        let bytecode = match bytecode {
+            Ok(bytecode) => bytecode,
+            Err(error) => {
+                self.detach(&start.dot_id);
+                return Err(error);
+            }
+        };
+
+        let (commands, receiver) = std::sync::mpsc::channel();
+        let code_len = bytecode.code.len() as u64;
+        let entry_point = bytecode.entry_point() as u64;
+        let hook = DebugHook::new(session_id.clone(), receiver, tx.clone());
+        let dot_id = start.dot_id.clone();
+        tokio::task::spawn_blocking(move || run_instrumented(dot_id, bytecode, hook));
+
+        info!("Debug session {} attached to dot {}", session_id, start.dot_id);
+        let started = DebugResponse {
+            response_type: Some(debug_response::ResponseType::Started(DebugSessionStarted {
+                session_id: session_id.clone(),
+                dot_id: start.dot_id.clone(),
+                initial_state: Some(ExecutionState {
+                    instruction_pointer: entry_point,
+                    stack_frames: vec![StackFrame {
+                        function_name: ENTRY_FUNCTION.to_string(),
+                        instruction_pointer: entry_point,
+                        local_variables: HashMap::new(),
+                    }],
+                    variables: HashMap::new(),
+                    memory_usage: 0,
+                }),
+            })),
+        };
+        let session = Session {
+            session_id,
+            dot_id: start.dot_id,
+            commands,
+            code_len,
+            entry_point,
+            breakpoints: HashMap::new(),
+            next_breakpoint_id: 1,
+        };
+        Ok((started, session))
+    }
+}
+
+/// Stream-side state of an active debug session
+struct Session {
+    session_id: String,
+    dot_id: String,
+    commands: Sender<Control>,
+    code_len: u64,
+    entry_point: u64,
+    /// Breakpoint IDs by absolute address
+    breakpoints: HashMap<u64, u64>,
+    next_breakpoint_id: u64,
+}
+
+impl Session {
+    fn send(&self, control: Control) -> Result<(), DebugResponse> {
+        self.commands
+            .send(control)
+            .map_err(|_| debug_error(&self.session_id, "EXECUTION_FINISHED", "The debugged execution has already finished"))
+    }
+
+    fn set_breakpoint(&mut self, request: SetBreakpoint) -> DebugResponse {
+        if !request.condition.is_empty() {
+            return debug_error(&self.session_id, "UNSUPPORTED", "Conditional breakpoints are not supported yet");
+        }
+        let address = match resolve_breakpoint(&request.function_name, request.instruction_address, self.entry_point, self.code_len) {
+            Ok(address) => address,
+            Err(message) => return debug_error(&self.session_id, "INVALID_BREAKPOINT", &message),
+        };
+
+        let breakpoint_id = if request.enabled {
+            *self.breakpoints.entry(address).or_insert_with(|| {
+                self.next_breakpoint_id += 1;
+                self.next_breakpoint_id - 1
+            })
+        } else {
+            match self.breakpoints.remove(&address) {
+                Some(id) => id,
+                None => return debug_error(&self.session_id, "INVALID_BREAKPOINT", &format!("No breakpoint set at {address:#06x}")),
+            }
+        };
+
+        if let Err(error) = self.send(Control::SetBreakpoint { address, enabled: request.enabled }) {
+            return error;
+        }
+        DebugResponse {
+            response_type: Some(debug_response::ResponseType::BreakpointSet(BreakpointSet {
+                session_id: self.session_id.clone(),
+                breakpoint_id,
+                instruction_address: address,
+                success: true,
+            })),
+        }
+    }
+
+    /// Forward an execution command, returning an error response if it cannot be run
+    fn command(&self, command: DebugCommand) -> Option<DebugResponse> {
+        let control = match DebugCommandType::try_from(command.command) {
+            Ok(DebugCommandType::DebugContinue) => Control::Continue,
+            // The VM has no call frames yet, so stepping over or into an instruction is the
+            // same single step and stepping out of the entry function runs to the end
+            Ok(DebugCommandType::DebugStepOver | DebugCommandType::DebugStepInto) => Control::Step,
+            Ok(DebugCommandType::DebugStepOut) => Control::Continue,
+            Ok(DebugCommandType::DebugPause) => Control::Pause,
+            _ => return Some(debug_error(&self.session_id, "UNKNOWN_COMMAND", &format!("Unknown debug command {}", command.command))),
+        };
+        self.send(control).err()
+    }
+}
+
+/// Absolute address of a breakpoint `offset` bytes into `function`
+fn resolve_breakpoint(function: &str, offset: u64, entry_point: u64, code_len: u64) -> Result<u64, String> {
+    let base = match function {
+        "" => 0,
+        ENTRY_FUNCTION => entry_point,
+        other => return Err(format!("Unknown function '{other}'; only '{ENTRY_FUNCTION}' is available")),
+    };
+    let address = base + offset;
+    if address >= code_len {
+        return Err(format!("Address {address:#06x} is outside the dot's {code_len} bytes of code"));
+    }
+    Ok(address)
+}
+
+/// Run the dot to completion on the current thread, reporting how it ended
+fn run_instrumented(dot_id: String, bytecode: BytecodeFile, hook: DebugHook) {
+    let session_id = hook.session_id.clone();
+    let responses = hook.responses.clone();
+
+    let mut executor = VmExecutor::new_with_dot_id(dot_id);
+    let result = executor.load_bytecode(bytecode).and_then(|_| {
+        executor.set_instruction_hook(Box::new(hook));
+        executor.execute()
+    });
+
+    let (event_type, message) = match result {
+        Ok(result) if result.halted => (DebugEventType::DebugEventExecutionComplete, format!("Execution halted after {} instructions", result.instructions_executed)),
+        Ok(result) => (DebugEventType::DebugEventExecutionComplete, format!("Execution finished after {} instructions", result.instructions_executed)),
+        Err(e) => {
+            warn!("Debugged execution in session {} failed: {}", session_id, e);
+            (DebugEventType::DebugEventException, e.to_string())
+        }
+    };
+    let _ = responses.send(Ok(debug_event(&session_id, event_type, executor.context(), message)));
+}
+
+/// Instruction hook driving a debugged execution
+#[derive(Debug)]
+struct DebugHook {
+    session_id: String,
+    commands: Receiver<Control>,
+    responses: ResponseSender,
+    breakpoints: HashSet<u64>,
+    /// Pause before the first instruction
+    at_entry: bool,
+    stepping: bool,
+    pause_requested: bool,
+}
+
+impl DebugHook {
+    fn new(session_id: String, commands: Receiver<Control>, responses: ResponseSender) -> Self {
+        Self {
+            session_id,
+            commands,
+            responses,
+            breakpoints: HashSet::new(),
+            at_entry: true,
+            stepping: false,
+            pause_requested: false,
+        }
+    }
+
+    fn respond(&self, response: DebugResponse) {
+        let _ = self.responses.send(Ok(response));
+    }
+
+    /// Block until a command resumes or stops execution
+    fn wait(&mut self, context: &ExecutionContext) -> HookAction {
+        loop {
+            match self.commands.recv() {
+                Ok(Control::Continue) => return HookAction::Continue,
+                Ok(Control::Step) => {
+                    self.stepping = true;
+                    return HookAction::Continue;
+                }
+                Ok(Control::Pause) => {}
+                Ok(Control::SetBreakpoint { address, enabled }) => self.set_breakpoint(address, enabled),
+                Ok(Control::Inspect(request)) => self.respond(inspect(&self.session_id, context, &request)),
+                Ok(Control::Stop) | Err(_) => return HookAction::Halt,
+            }
+        }
+    }
+
+    fn set_breakpoint(&mut self, address: u64, enabled: bool) {
+        if enabled {
+            self.breakpoints.insert(address);
+        } else {
+            self.breakpoints.remove(&address);
+        }
+    }
+}
+
+impl InstructionHook for DebugHook {
+    fn before_instruction(&mut self, context: &ExecutionContext, _instruction: &Instruction) -> HookAction {
+        let pc = context.pc as u64;
+
+        // Held before the first instruction; the session start already reported this state
+        if std::mem::take(&mut self.at_entry) {
+            if self.breakpoints.contains(&pc) {
+                self.respond(debug_event(&self.session_id, DebugEventType::DebugEventBreakpoint, context, format!("Breakpoint hit at {pc:#06x}")));
+            }
+            return self.wait(context);
+        }
+
+        // Handle commands that arrived while running. Continue and step only mean
+        // something while paused, so they are dropped here.
+        loop {
+            match self.commands.try_recv() {
+                Ok(Control::SetBreakpoint { address, enabled }) => self.set_breakpoint(address, enabled),
+                Ok(Control::Pause) => self.pause_requested = true,
+                Ok(Control::Inspect(_)) => self.respond(debug_error(&self.session_id, "NOT_PAUSED", "Execution is running; pause it before inspecting state")),
+                Ok(Control::Continue | Control::Step) => {}
+                Ok(Control::Stop) | Err(TryRecvError::Disconnected) => return HookAction::Halt,
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+
+        let stepped = std::mem::take(&mut self.stepping);
+        let requested = std::mem::take(&mut self.pause_requested);
+        let event = if self.breakpoints.contains(&pc) {
+            Some((DebugEventType::DebugEventBreakpoint, format!("Breakpoint hit at {pc:#06x}")))
+        } else if stepped {
+            Some((DebugEventType::DebugEventStepComplete, format!("Stepped to {pc:#06x}")))
+        } else if requested {
+            Some((DebugEventType::DebugEventPaused, format!("Paused at {pc:#06x}")))
+        } else {
+            None
+        };
+
+        match event {
+            Some((event_type, message)) => {
+                self.respond(debug_event(&self.session_id, event_type, context, message));
+                self.wait(context)
+            }
+            None => HookAction::Continue,
+        }
+    }
+}
+
+/// Snapshot of the VM state. Locals are reported on the single stack frame; `variables`
+/// holds the operand stack as `stack[N]`, where `stack[0]` is the top.
+fn execution_state(context: &ExecutionContext) -> ExecutionState {
+    let local_variables = context.locals.iter().map(|(name, value)| (name.clone(), encode_value(value))).collect();
+    let variables = context.stack.snapshot().iter().rev().enumerate().map(|(depth, value)| (format!("stack[{depth}]"), encode_value(value))).collect();
+
+    ExecutionState {
+        instruction_pointer: context.pc as u64,
+        stack_frames: vec![StackFrame {
+            function_name: ENTRY_FUNCTION.to_string(),
+            instruction_pointer: context.pc as u64,
+            local_variables,
+        }],
+        variables,
+        memory_usage: context.resource_usage.memory_bytes,
+    }
+}
+
+/// Values are sent as their JSON encoding
+fn encode_value(value: &StackValue) -> Vec<u8> {
+    serde_json::to_vec(&value.to_json()).unwrap_or_default()
+}
+
+fn inspection(session_id: &str, name: String, value: &StackValue) -> VariableInspection {
+    VariableInspection {
+        session_id: session_id.to_string(),
+        variable_name: name,
+        value: encode_value(value),
+        type_info: value.type_name().to_string(),
+        children: vec![],
+    }
+}
+
+/// Read a local (scope `local` or empty) or operand stack entry (scope `stack`, named by
+/// depth from the top). An empty name returns every entry of the scope as children.
+fn inspect(session_id: &str, context: &ExecutionContext, request: &InspectVariable) -> DebugResponse {
+    let name = request.variable_name.as_str();
+    let inspection = match (request.scope.as_str(), name) {
+        ("stack", "") => Ok(VariableInspection {
+            session_id: session_id.to_string(),
+            variable_name: String::new(),
+            value: vec![],
+            type_info: "stack".to_string(),
+            children: (0..context.stack.size())
+                .filter_map(|depth| context.stack.peek_at(depth).ok().map(|value| inspection(session_id, depth.to_string(), value)))
+                .collect(),
+        }),
+        ("stack", depth) => match depth.parse::<usize>().ok().and_then(|depth| context.stack.peek_at(depth).ok()) {
+            Some(value) => Ok(inspection(session_id, depth.to_string(), value)),
+            None => Err(format!("No operand stack entry at depth '{depth}' (stack holds {} values)", context.stack.size())),
+        },
+        ("local" | "", "") => {
+            let mut children: Vec<_> = context.locals.iter().map(|(name, value)| inspection(session_id, name.clone(), value)).collect();
+            children.sort_by(|a, b| a.variable_name.cmp(&b.variable_name));
+            Ok(VariableInspection {
+                session_id: session_id.to_string(),
+                variable_name: String::new(),
+                value: vec![],
+                type_info: "locals".to_string(),
+                children,
+            })
+        }
+        ("local" | "", name) => match context.locals.get(name) {
+            Some(value) => Ok(inspection(session_id, name.to_string(), value)),
+            None => Err(format!("No local variable named '{name}'")),
+        },
+        (scope, _) => Err(format!("Unknown scope '{scope}', expected 'local' or 'stack'")),
+    };
+
+    match inspection {
+        Ok(inspection) => DebugResponse {
+            response_type: Some(debug_response::ResponseType::Inspection(inspection)),
+        },
+        Err(message) => debug_error(session_id, "NOT_FOUND", &message),
+    }
+}
+
+fn debug_event(session_id: &str, event_type: DebugEventType, context: &ExecutionContext, message: String) -> DebugResponse {
+    DebugResponse {
+        response_type: Some(debug_response::ResponseType::Event(DebugEvent {
+            session_id: session_id.to_string(),
+            event_type: event_type as i32,
+            current_state: Some(execution_state(context)),
+            message,
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+        })),
+    }
+}
+
+fn debug_error(session_id: &str, error_code: &str, error_message: &str) -> DebugResponse {
+    DebugResponse {
+        response_type: Some(debug_response::ResponseType::Error(DebugError {
+            session_id: session_id.to_string(),
+            error_code: error_code.to_string(),
+            error_message: error_message.to_string(),
+        })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dotvm_core::opcode::stack_opcodes::{StackInstruction, StackOpcode};
+
+    fn instruction() -> Instruction {
+        Instruction::Stack(StackInstruction::new(StackOpcode::Dup, vec![]))
+    }
+
+    fn context_at(pc: usize) -> ExecutionContext {
+        let mut context = ExecutionContext::new();
+        context.pc = pc;
+        context
+    }
+
+    fn next_response(rx: &mut tokio::sync::mpsc::UnboundedReceiver<Result<DebugResponse, Status>>) -> debug_response::ResponseType {
+        rx.try_recv().unwrap().unwrap().response_type.unwrap()
+    }
+
+    #[test]
+    fn test_attach_rejects_second_session() {
+        let debugger = DotDebugger::new();
+        debugger.attach("dot", "first").unwrap();
+        assert_eq!(debugger.attach("dot", "second").unwrap_err(), "first");
+        debugger.attach("other", "second").unwrap();
+
+        debugger.detach("dot");
+        debugger.attach("dot", "third").unwrap();
+    }
+
+    #[test]
+    fn test_resolve_breakpoint() {
+        assert_eq!(resolve_breakpoint(ENTRY_FUNCTION, 4, 0, 10), Ok(4));
+        assert_eq!(resolve_breakpoint("", 9, 0, 10), Ok(9));
+        assert!(resolve_breakpoint("", 10, 0, 10).is_err());
+        assert!(resolve_breakpoint("helper", 0, 0, 10).unwrap_err().contains("Unknown function"));
+    }
+
+    #[test]
+    fn test_hook_pauses_at_breakpoints_and_steps() {
+        let (commands, receiver) = std::sync::mpsc::channel();
+        let (tx, mut rx) = unbounded_channel();
+        let vm = std::thread::spawn(move || {
+            let mut hook = DebugHook::new("session".to_string(), receiver, tx);
+            [0, 2, 4, 5].into_iter().map(|pc| hook.before_instruction(&context_at(pc), &instruction())).collect::<Vec<_>>()
+        });
+
+        // Held at entry until continued, then runs freely until the breakpoint
+        commands.send(Control::SetBreakpoint { address: 4, enabled: true }).unwrap();
+        commands.send(Control::Continue).unwrap();
+        match rx.blocking_recv().unwrap().unwrap().response_type.unwrap() {
+            debug_response::ResponseType::Event(event) => {
+                assert_eq!(event.event_type, DebugEventType::DebugEventBreakpoint as i32);
+                assert_eq!(event.current_state.unwrap().instruction_pointer, 4);
+            }
+            other => panic!("Expected breakpoint event, got {other:?}"),
+        }
+
+        // A step pauses on the next instruction, where stopping halts the VM
+        commands.send(Control::Step).unwrap();
+        match rx.blocking_recv().unwrap().unwrap().response_type.unwrap() {
+            debug_response::ResponseType::Event(event) => {
+                assert_eq!(event.event_type, DebugEventType::DebugEventStepComplete as i32);
+                assert_eq!(event.current_state.unwrap().instruction_pointer, 5);
+            }
+            other => panic!("Expected step event, got {other:?}"),
+        }
+        commands.send(Control::Stop).unwrap();
+
+        let actions = vm.join().unwrap();
+        assert_eq!(actions, vec![HookAction::Continue, HookAction::Continue, HookAction::Continue, HookAction::Halt]);
+    }
+
+    #[test]
+    fn test_hook_inspects_paused_state() {
+        let (commands, receiver) = std::sync::mpsc::channel();
+        let (tx, mut rx) = unbounded_channel();
+        let mut hook = DebugHook::new("session".to_string(), receiver, tx);
+
+        let mut context = context_at(0);
+        context.stack.push(StackValue::Int64(1)).unwrap();
+        context.stack.push(StackValue::String("top".to_string())).unwrap();
+        context.locals.insert("count".to_string(), StackValue::Int64(7));
+
+        let request = |scope: &str, name: &str| {
+            Control::Inspect(InspectVariable {
+                session_id: "session".to_string(),
+                variable_name: name.to_string(),
+                scope: scope.to_string(),
+            })
+        };
+        commands.send(request("stack", "0")).unwrap();
+        commands.send(request("local", "count")).unwrap();
+        commands.send(request("stack", "")).unwrap();
+        commands.send(request("local", "missing")).unwrap();
+        commands.send(Control::Continue).unwrap();
+        assert_eq!(hook.before_instruction(&context, &instruction()), HookAction::Continue);
+
+        match next_response(&mut rx) {
+            debug_response::ResponseType::Inspection(inspection) => {
+                assert_eq!(inspection.value, br#""top""#.to_vec());
+                assert_eq!(inspection.type_info, "string");
+            }
+            other => panic!("Expected inspection, got {other:?}"),
+        }
+        match next_response(&mut rx) {
+            debug_response::ResponseType::Inspection(inspection) => assert_eq!(inspection.value, b"7".to_vec()),
+            other => panic!("Expected inspection, got {other:?}"),
+        }
+        match next_response(&mut rx) {
+            debug_response::ResponseType::Inspection(inspection) => assert_eq!(inspection.children.len(), 2),
+            other => panic!("Expected inspection, got {other:?}"),
+        }
+        assert!(matches!(next_response(&mut rx), debug_response::ResponseType::Error(_)));
+
+        // Inspecting while running is refused
+        commands.send(request("stack", "0")).unwrap();
+        assert_eq!(hook.before_instruction(&context_at(1), &instruction()), HookAction::Continue);
+        match next_response(&mut rx) {
+            debug_response::ResponseType::Error(error) => assert_eq!(error.error_code, "NOT_PAUSED"),
+            other => panic!("Expected error, got {other:?}"),
+        }
+    }
+}
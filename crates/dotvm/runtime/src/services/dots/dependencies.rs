@@ -0,0 +1,169 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Version constraints on dot dependencies
+//!
+//! A constraint is a comma-separated list of comparisons that must all hold,
+//! such as `>=1.2, <2`. Versions are dotted numbers; missing components count
+//! as zero and anything after `-` or `+` is ignored. Besides `=`, `>`, `>=`,
+//! `<` and `<=`, `^1.2` accepts `1.2` and later within major version 1 and
+//! `~1.2` accepts `1.2` and later within `1.2`. A bare version is an exact
+//! match on the components given, so `1.2` accepts `1.2.0` and `1.2.7`.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Exact,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Caret,
+    Tilde,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Comparator {
+    op: Op,
+    version: Vec<u64>,
+}
+
+impl Comparator {
+    fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let (op, version) = [
+            (">=", Op::GreaterOrEqual),
+            ("<=", Op::LessOrEqual),
+            ("==", Op::Exact),
+            (">", Op::Greater),
+            ("<", Op::Less),
+            ("=", Op::Exact),
+            ("^", Op::Caret),
+            ("~", Op::Tilde),
+        ]
+        .into_iter()
+        .find_map(|(prefix, op)| text.strip_prefix(prefix).map(|rest| (op, rest)))
+        .unwrap_or((Op::Exact, text));
+
+        let version = parse_version(version.trim()).ok_or_else(|| format!("'{text}' is not a version comparison"))?;
+        Ok(Self { op, version })
+    }
+
+    fn matches(&self, version: &[u64]) -> bool {
+        let ordering = compare(version, &self.version);
+        match self.op {
+            Op::Exact => self.version.iter().enumerate().all(|(i, &part)| component(version, i) == part),
+            Op::Greater => ordering == Ordering::Greater,
+            Op::GreaterOrEqual => ordering != Ordering::Less,
+            Op::Less => ordering == Ordering::Less,
+            Op::LessOrEqual => ordering != Ordering::Greater,
+            Op::Caret => ordering != Ordering::Less && component(version, 0) == component(&self.version, 0),
+            Op::Tilde => ordering != Ordering::Less && (0..2).all(|i| component(version, i) == component(&self.version, i)),
+        }
+    }
+}
+
+/// Versions of a dependency a dot accepts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionConstraint {
+    text: String,
+    comparators: Vec<Comparator>,
+}
+
+impl VersionConstraint {
+    /// Parse a constraint; an empty one accepts any version
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let comparators = if text.is_empty() {
+            Vec::new()
+        } else {
+            text.split(',').map(Comparator::parse).collect::<Result<_, _>>()?
+        };
+        Ok(Self { text: text.to_string(), comparators })
+    }
+
+    /// Whether `version` satisfies every comparison; versions that do not parse never do
+    pub fn matches(&self, version: &str) -> bool {
+        match parse_version(version.trim()) {
+            Some(version) => self.comparators.iter().all(|comparator| comparator.matches(&version)),
+            None => self.comparators.is_empty(),
+        }
+    }
+
+    /// Whether every version is accepted
+    pub fn is_any(&self) -> bool {
+        self.comparators.is_empty()
+    }
+}
+
+impl fmt::Display for VersionConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+fn parse_version(text: &str) -> Option<Vec<u64>> {
+    let numeric = text.split(['-', '+']).next()?;
+    numeric.split('.').map(|part| part.parse().ok()).collect()
+}
+
+fn component(version: &[u64], index: usize) -> u64 {
+    version.get(index).copied().unwrap_or(0)
+}
+
+fn compare(a: &[u64], b: &[u64]) -> Ordering {
+    (0..a.len().max(b.len()))
+        .map(|i| component(a, i).cmp(&component(b, i)))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constraint_matching() {
+        let range = VersionConstraint::parse(">=1.2, <2").unwrap();
+        assert!(range.matches("1.2.0"));
+        assert!(range.matches("1.9.4-beta"));
+        assert!(!range.matches("1.1.9"));
+        assert!(!range.matches("2.0.0"));
+
+        let exact = VersionConstraint::parse("1.2").unwrap();
+        assert!(exact.matches("1.2.7"));
+        assert!(!exact.matches("1.3.0"));
+
+        assert!(VersionConstraint::parse("^1.2").unwrap().matches("1.8"));
+        assert!(!VersionConstraint::parse("^1.2").unwrap().matches("2.0"));
+        assert!(VersionConstraint::parse("~1.2").unwrap().matches("1.2.5"));
+        assert!(!VersionConstraint::parse("~1.2").unwrap().matches("1.3.0"));
+
+        // Registry version numbers are single-component versions
+        assert!(VersionConstraint::parse(">=3").unwrap().matches("4"));
+        assert!(VersionConstraint::parse("").unwrap().matches("anything"));
+        assert!(!range.matches("latest"));
+    }
+
+    #[test]
+    fn test_rejects_malformed_constraints() {
+        assert!(VersionConstraint::parse(">=").is_err());
+        assert!(VersionConstraint::parse("1.x").is_err());
+        assert!(VersionConstraint::parse(">=1,").is_err());
+    }
+}
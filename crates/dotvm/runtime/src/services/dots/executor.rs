@@ -16,15 +16,27 @@
 
 //! Dot executor - handles dot execution and state management
 
-use std::collections::HashMap;
+use dotvm_core::bytecode::VmArchitecture;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use thiserror::Error;
-use tracing::{error, info, instrument};
+use tracing::{Instrument, error, info, info_span, instrument, warn};
 
-use crate::proto::vm_service::{ExecuteDotRequest, ExecuteDotResponse, ExecutionMetrics, GetDotStateRequest, GetDotStateResponse, LogEntry};
+use crate::proto::vm_service::{
+    AbiField, DotInfo, ExecuteDotRequest, ExecuteDotResponse, ExecutionMetrics, GetDotStateRequest, GetDotStateResponse, GetStateDiffRequest, GetStateDiffResponse, LogEntry, StateChange, StateValue,
+    state_change, state_value,
+};
 
+use super::architecture::{ArchitectureConfig, ArchitectureError};
+use super::interactive::{InputPort, InputPrompt};
+use super::memo::{CacheKey, ResultCache};
 use super::paradots::ParaDotManager;
+use super::recording::ExecutionRecorder;
 use super::registry::StoredDot;
+use super::state::{ChangeKind, DiffQuery, DiffValue, DotStateStore, StateQuery, StateStoreError};
+use crate::services::metrics::latency::{Latency, LatencyHistograms};
 
 #[derive(Error, Debug)]
 pub enum ExecutorError {
@@ -36,38 +48,316 @@ pub enum ExecutorError {
     ResourceLimitExceeded,
     #[error("State error: {0}")]
     StateError(String),
+    #[error(transparent)]
+    StateStore(#[from] StateStoreError),
+    #[error("No input received within {0:?}")]
+    InputTimeout(Duration),
+    #[error("Interactive session closed while waiting for input")]
+    SessionClosed,
+    #[error("Dot {0} awaited input outside an interactive execution")]
+    NotInteractive(String),
+    #[error("Dot {dot_id} is declared deterministic but made a non-deterministic {call} host call")]
+    NonDeterministic { dot_id: String, call: HostCall },
+    #[error(transparent)]
+    Architecture(#[from] ArchitectureError),
+}
+
+/// Input a ParaDot's code receives its payload in, and output it returns its result in
+const PARADOT_PAYLOAD: &str = "payload";
+
+/// A call from a running dot into the host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostCall {
+    Log,
+    Clock,
+    Random,
+    DatabaseRead,
+    DatabaseWrite,
+    StateRead,
+    StateWrite,
+    AwaitInput,
+}
+
+impl HostCall {
+    /// Whether the call's effect on the outputs is fixed by the inputs
+    ///
+    /// Reads count as non-deterministic too: the data they return can change
+    /// between executions with the same inputs. So does input awaited from the caller.
+    pub fn is_deterministic(self) -> bool {
+        matches!(self, HostCall::Log)
+    }
+}
+
+impl fmt::Display for HostCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            HostCall::Log => "log",
+            HostCall::Clock => "clock",
+            HostCall::Random => "random",
+            HostCall::DatabaseRead => "database read",
+            HostCall::DatabaseWrite => "database write",
+            HostCall::StateRead => "state read",
+            HostCall::StateWrite => "state write",
+            HostCall::AwaitInput => "await input",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Host calls made by one execution
+///
+/// For a dot declared deterministic the first non-deterministic call fails the
+/// execution, so a result that depends on anything but the inputs is never cached.
+pub struct HostCalls<'a> {
+    dot_id: String,
+    deterministic: bool,
+    calls: Vec<HostCall>,
+    /// State writes (`Some`) and deletions (`None`), committed once the execution succeeds
+    writes: BTreeMap<String, Option<Vec<u8>>>,
+    /// Caller of an interactive execution, answering `await_input`
+    input: Option<&'a mut InputPort>,
+    /// Inputs received through `await_input`, later answers winning
+    received: HashMap<String, Vec<u8>>,
+}
+
+impl<'a> HostCalls<'a> {
+    pub fn new(dot_id: &str, deterministic: bool) -> Self {
+        Self {
+            dot_id: dot_id.to_string(),
+            deterministic,
+            calls: Vec::new(),
+            writes: BTreeMap::new(),
+            input: None,
+            received: HashMap::new(),
+        }
+    }
+
+    /// Serve `await_input` by prompting the caller through `input`
+    pub fn with_input(mut self, input: &'a mut InputPort) -> Self {
+        self.input = Some(input);
+        self
+    }
+
+    /// Suspend the execution until the caller answers `prompt`, or its input timeout expires
+    pub async fn await_input(&mut self, prompt: InputPrompt) -> Result<HashMap<String, Vec<u8>>, ExecutorError> {
+        self.call(HostCall::AwaitInput)?;
+        let input = self.input.as_mut().ok_or_else(|| ExecutorError::NotInteractive(self.dot_id.clone()))?;
+        let inputs = input.await_input(prompt).await?;
+        self.received.extend(inputs.clone());
+        Ok(inputs)
+    }
+
+    /// Buffer a write to the dot's state, or its deletion for `None`
+    pub fn write_state(&mut self, key: String, value: Option<Vec<u8>>) -> Result<(), ExecutorError> {
+        self.call(HostCall::StateWrite)?;
+        self.writes.insert(key, value);
+        Ok(())
+    }
+
+    /// State changes buffered so far, the last write to each key winning
+    pub fn writes(&self) -> &BTreeMap<String, Option<Vec<u8>>> {
+        &self.writes
+    }
+
+    /// Record a call before the host serves it
+    pub fn call(&mut self, call: HostCall) -> Result<(), ExecutorError> {
+        if self.deterministic && !call.is_deterministic() {
+            return Err(ExecutorError::NonDeterministic { dot_id: self.dot_id.clone(), call });
+        }
+        self.calls.push(call);
+        Ok(())
+    }
+
+    pub fn calls(&self) -> &[HostCall] {
+        &self.calls
+    }
+
+    fn take_writes(&mut self) -> BTreeMap<String, Option<Vec<u8>>> {
+        std::mem::take(&mut self.writes)
+    }
+
+    fn take_received(&mut self) -> HashMap<String, Vec<u8>> {
+        std::mem::take(&mut self.received)
+    }
 }
 
 /// Dot executor handles execution of deployed dots
 pub struct DotExecutor {
     paradot_manager: Arc<ParaDotManager>,
-    // TODO: Add VM instance, state storage, etc.
+    state_store: Arc<DotStateStore>,
+    result_cache: Arc<ResultCache>,
+    recorder: Arc<ExecutionRecorder>,
+    latencies: Arc<LatencyHistograms>,
+    /// Architectures there are executors for
+    architectures: ArchitectureConfig,
+    // TODO: Add VM instance
 }
 
 impl DotExecutor {
     pub fn new() -> Self {
+        Self::with_result_cache(Arc::new(ResultCache::default()))
+    }
+
+    pub fn with_result_cache(result_cache: Arc<ResultCache>) -> Self {
         Self {
             paradot_manager: Arc::new(ParaDotManager::new()),
+            state_store: Arc::new(DotStateStore::default()),
+            result_cache,
+            recorder: Arc::new(ExecutionRecorder::default()),
+            latencies: Arc::new(LatencyHistograms::default()),
+            architectures: ArchitectureConfig::default(),
         }
     }
 
-    #[instrument(skip(self, dot_info, request))]
+    /// Record executions through `recorder` instead of into an in-memory trace store
+    pub fn with_recorder(mut self, recorder: Arc<ExecutionRecorder>) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
+    /// Record per-dot execution and state access times into `latencies`
+    pub fn with_latencies(mut self, latencies: Arc<LatencyHistograms>) -> Self {
+        self.latencies = latencies;
+        self
+    }
+
+    /// Keep dot state in `state_store`, e.g. one persisted in a dotdb database
+    pub fn with_state_store(mut self, state_store: Arc<DotStateStore>) -> Self {
+        self.state_store = state_store;
+        self
+    }
+
+    /// Run dots on the configured architectures only
+    pub fn with_architectures(mut self, architectures: ArchitectureConfig) -> Self {
+        self.architectures = architectures;
+        self
+    }
+
+    /// Versioned state of every deployed dot
+    pub fn state_store(&self) -> &Arc<DotStateStore> {
+        &self.state_store
+    }
+
+    /// Memoized results of deterministic dots
+    pub fn result_cache(&self) -> &Arc<ResultCache> {
+        &self.result_cache
+    }
+
+    /// Records executions for deterministic replay
+    pub fn recorder(&self) -> &Arc<ExecutionRecorder> {
+        &self.recorder
+    }
+
+    /// Per-dot latency histograms
+    pub fn latencies(&self) -> &Arc<LatencyHistograms> {
+        &self.latencies
+    }
+
+    pub fn architectures(&self) -> &ArchitectureConfig {
+        &self.architectures
+    }
+
+    /// Execute a dot, serving deterministic dots from the result cache when possible
+    #[instrument(skip(self, dot_info, request), fields(dot_id = %dot_info.info.dot_id, version = dot_info.version))]
     pub async fn execute(&self, dot_info: &StoredDot, request: ExecuteDotRequest) -> Result<ExecuteDotResponse, ExecutorError> {
+        self.run(dot_info, request, None).await
+    }
+
+    /// Execute a dot that can suspend on the `await_input` host call until its caller answers
+    ///
+    /// Required ABI inputs the request leaves out are not an error here: the dot awaits them.
+    #[instrument(skip(self, dot_info, request, input), fields(dot_id = %dot_info.info.dot_id, version = dot_info.version))]
+    pub async fn execute_interactive(&self, dot_info: &StoredDot, request: ExecuteDotRequest, input: &mut InputPort) -> Result<ExecuteDotResponse, ExecutorError> {
+        self.run(dot_info, request, Some(input)).await
+    }
+
+    async fn run(&self, dot_info: &StoredDot, mut request: ExecuteDotRequest, input: Option<&mut InputPort>) -> Result<ExecuteDotResponse, ExecutorError> {
         info!("Executing dot: {} with {} inputs", dot_info.info.dot_id, request.inputs.len());
 
         // Validate inputs against ABI
-        if let Some(abi) = &dot_info.abi {
+        if let Some(abi) = &dot_info.abi
+            && input.is_none()
+        {
+            let _span = info_span!("validation", stage = "inputs").entered();
             self.validate_inputs(&request.inputs, abi)?;
         }
 
+        // Pick the executor for the dot's architecture before anything is served or run
+        let selected = self.architectures.select(dot_info.architecture)?;
+        if selected.compatibility_mode {
+            info!("Running {} bytecode of dot {} on {} in compatibility mode", selected.required, dot_info.info.dot_id, selected.execution);
+        }
+
+        let dot_id = &dot_info.info.dot_id;
+        let deterministic = dot_info.abi.as_ref().is_some_and(|abi| abi.deterministic);
+        // A recorded execution always runs, so its trace holds the host calls it made
+        let recording = self.recorder.should_record(dot_id, &request);
+        let cache_key = (deterministic && self.result_cache.is_enabled(dot_id)).then(|| CacheKey::new(dot_id, dot_info.version, &request.inputs));
+        if let Some(key) = cache_key.as_ref().filter(|_| !recording) {
+            let lookup_started = Instant::now();
+            if request.options.as_ref().is_some_and(|options| options.bypass_cache) {
+                self.result_cache.record_bypass(dot_id);
+            } else if let Some(mut cached) = self.result_cache.get(key) {
+                info!("Serving dot {} version {} from the result cache", dot_id, dot_info.version);
+                cached.cached = true;
+                cached.execution_time_ms = lookup_started.elapsed().as_millis() as u64;
+                return Ok(cached);
+            }
+        }
+
+        // Pin the state version the execution starts from; dots deployed without state have none
+        let state_started = Instant::now();
+        let state_version = info_span!("state_access").in_scope(|| self.state_store.current_version(&dot_info.info.dot_id).ok());
+        self.latencies.record(dot_id, Latency::StateAccess, state_started.elapsed());
+        info!("Dot {} starts from state version {:?}", dot_info.info.dot_id, state_version);
+
         // Execute bytecode in VM with automatic ParaDot coordination
-        let execution_result = self.execute_bytecode(&dot_info.bytecode, &request).await?;
+        let mut host = HostCalls::new(dot_id, deterministic);
+        if let Some(input) = input {
+            host = host.with_input(input);
+        }
+        let execution_started = Instant::now();
+        let execution = self
+            .execute_bytecode(dot_info, selected.execution, &request, &mut host)
+            .instrument(info_span!("execution", architecture = %selected.execution))
+            .await;
+        self.latencies.record(dot_id, Latency::Execution, execution_started.elapsed());
+        let mut execution_result = execution?;
+        execution_result.dot_version = dot_info.version;
+        // Awaited inputs are part of what the execution ran with, e.g. for a recording to replay
+        request.inputs.extend(host.take_received());
 
         // Validate outputs against ABI
         if let Some(abi) = &dot_info.abi {
+            let _span = info_span!("validation", stage = "outputs").entered();
             self.validate_outputs(&execution_result.outputs, abi)?;
         }
 
+        // Writes become a new state version only once the execution has succeeded and validated
+        let writes = host.take_writes();
+        if execution_result.success && state_version.is_some() && !writes.is_empty() {
+            let state_started = Instant::now();
+            let description = format!("Execution of version {} by {}", dot_info.version, request.caller_id);
+            let committed = info_span!("state_commit", writes = writes.len()).in_scope(|| self.state_store.commit(dot_id, writes, description));
+            self.latencies.record(dot_id, Latency::StateAccess, state_started.elapsed());
+            info!("Dot {} committed state version {}", dot_id, committed?);
+        }
+
+        if recording {
+            match self.recorder.record(dot_info, &request, state_version, &execution_result).instrument(info_span!("recording")).await {
+                Ok(trace_id) => execution_result.trace_id = trace_id,
+                Err(e) => warn!("Failed to record execution of dot {}: {}", dot_id, e),
+            }
+        }
+
+        if let Some(key) = cache_key
+            && execution_result.success
+        {
+            let mut cached = execution_result.clone();
+            cached.trace_id.clear();
+            self.result_cache.insert(key, cached);
+        }
+
         Ok(execution_result)
     }
 
@@ -75,27 +365,142 @@ impl DotExecutor {
     pub async fn get_state(&self, request: GetDotStateRequest) -> Result<GetDotStateResponse, ExecutorError> {
         info!("Getting state for dot: {}", request.dot_id);
 
-        // TODO: Implement state retrieval from storage
+        let version = match request.version.trim() {
+            "" => None,
+            version => Some(version.parse::<u64>().map_err(|_| ExecutorError::InvalidInput(format!("Invalid state version: {version}")))?),
+        };
+        let query = StateQuery {
+            version,
+            keys: request.keys,
+            key_prefix: request.key_prefix,
+            start_after: Some(request.start_after).filter(|key| !key.is_empty()),
+            limit: request.limit as usize,
+        };
+
+        let page = self.state_store.read(&request.dot_id, &query)?;
+
         Ok(GetDotStateResponse {
             success: true,
-            state_data: HashMap::new(),
-            state_root_hash: "mock_root_hash".to_string(),
-            version: 1,
+            state_data: page.entries.into_iter().collect(),
+            state_root_hash: hex::encode(page.root_hash),
+            version: page.version,
             error_message: String::new(),
+            has_more: page.next_key.is_some(),
+            next_key: page.next_key.unwrap_or_default(),
+        })
+    }
+
+    #[instrument(skip(self, request))]
+    pub async fn get_state_diff(&self, request: GetStateDiffRequest) -> Result<GetStateDiffResponse, ExecutorError> {
+        info!("Diffing state for dot: {} ({} -> {})", request.dot_id, request.from_version, request.to_version);
+
+        let query = DiffQuery {
+            start_after: Some(request.start_after).filter(|key| !key.is_empty()),
+            limit: request.limit as usize,
+            max_inline_value_size: Some(request.max_inline_value_size as usize).filter(|&size| size > 0),
+        };
+
+        let diff = self.state_store.diff_versions(&request.dot_id, request.from_version, request.to_version, &query)?;
+
+        let value = |value: DiffValue| match value {
+            DiffValue::Inline(bytes) => StateValue {
+                size: bytes.len() as u64,
+                value: Some(state_value::Value::Inline(bytes)),
+            },
+            DiffValue::Hashed { sha256, size } => StateValue {
+                size: size as u64,
+                value: Some(state_value::Value::Sha256(hex::encode(sha256))),
+            },
+        };
+        let changes = diff
+            .changes
+            .into_iter()
+            .map(|change| StateChange {
+                key: change.key,
+                kind: match change.kind {
+                    ChangeKind::Added => state_change::Kind::Added,
+                    ChangeKind::Removed => state_change::Kind::Removed,
+                    ChangeKind::Modified => state_change::Kind::Modified,
+                } as i32,
+                old_value: change.old_value.map(value),
+                new_value: change.new_value.map(value),
+            })
+            .collect();
+
+        Ok(GetStateDiffResponse {
+            from_version: diff.from_version,
+            to_version: diff.to_version,
+            from_root_hash: hex::encode(diff.from_root),
+            to_root_hash: hex::encode(diff.to_root),
+            changes,
+            has_more: diff.next_key.is_some(),
+            next_key: diff.next_key.unwrap_or_default(),
         })
     }
 
     // Private methods
-    async fn execute_bytecode(&self, bytecode: &[u8], request: &ExecuteDotRequest) -> Result<ExecuteDotResponse, ExecutorError> {
-        info!("Executing bytecode ({} bytes)", bytecode.len());
+    /// Run a ParaDot's bytecode on behalf of its parent dot, returning its output
+    ///
+    /// The code runs on the same VM path as dots, with `payload` as its only input. It runs
+    /// deterministically, so it cannot write its parent's state, and is neither memoized nor recorded.
+    pub async fn execute_paradot(&self, paradot_id: &str, bytecode: Vec<u8>, payload: Vec<u8>) -> Result<Vec<u8>, ExecutorError> {
+        let architecture = ArchitectureConfig::detect(&bytecode).unwrap_or(self.architectures.default);
+        let selected = self.architectures.select(architecture)?;
+        let paradot = StoredDot {
+            info: DotInfo {
+                dot_id: paradot_id.to_string(),
+                ..Default::default()
+            },
+            version: 0,
+            source: String::new(),
+            bytecode,
+            abi: None,
+            architecture,
+        };
+        let request = ExecuteDotRequest {
+            dot_id: paradot_id.to_string(),
+            inputs: HashMap::from([(PARADOT_PAYLOAD.to_string(), payload)]),
+            ..Default::default()
+        };
+
+        let mut host = HostCalls::new(paradot_id, true);
+        let mut response = self.execute_bytecode(&paradot, selected.execution, &request, &mut host).await?;
+        Ok(response.outputs.remove(PARADOT_PAYLOAD).unwrap_or_default())
+    }
+
+    async fn execute_bytecode(&self, dot_info: &StoredDot, architecture: VmArchitecture, request: &ExecuteDotRequest, host: &mut HostCalls<'_>) -> Result<ExecuteDotResponse, ExecutorError> {
+        info!("Executing bytecode ({} bytes) on {}", dot_info.bytecode.len(), architecture);
 
         // TODO: Implement actual VM execution
         // For now, return mock execution result
 
         let start_time = std::time::Instant::now();
 
-        // Mock execution - echo inputs as outputs
-        let outputs = request.inputs.clone();
+        // Mock execution - await required inputs that are missing, then echo the inputs as outputs,
+        // keeping them as state unless the dot is deterministic
+        let mut outputs = request.inputs.clone();
+        if let Some(abi) = &dot_info.abi {
+            loop {
+                let missing: Vec<AbiField> = abi.inputs.iter().filter(|field| field.required && !outputs.contains_key(&field.name)).cloned().collect();
+                if missing.is_empty() {
+                    break;
+                }
+
+                let names: Vec<&str> = missing.iter().map(|field| field.name.as_str()).collect();
+                let prompt = InputPrompt {
+                    message: format!("Dot {} is waiting for: {}", dot_info.info.dot_id, names.join(", ")),
+                    schema: missing,
+                };
+                outputs.extend(host.await_input(prompt).await?);
+            }
+        }
+        let input_count = outputs.len();
+        host.call(HostCall::Log)?;
+        if !host.deterministic {
+            for (key, value) in &outputs {
+                host.write_state(key.clone(), Some(value.clone()))?;
+            }
+        }
 
         let execution_time = start_time.elapsed().as_millis() as u64;
 
@@ -106,21 +511,24 @@ impl DotExecutor {
             paradots_used: vec!["mock_paradot".to_string()],
             logs: vec![LogEntry {
                 level: "info".to_string(),
-                message: format!("Executed dot with {} inputs", request.inputs.len()),
+                message: format!("Executed dot with {} inputs on {}", input_count, architecture),
                 timestamp: chrono::Utc::now().timestamp() as u64,
                 source: "dot_executor".to_string(),
                 context: HashMap::new(),
             }],
             events: vec![],
             error_message: String::new(),
+            dot_version: 0,
+            cached: false,
             metrics: Some(ExecutionMetrics {
                 instructions_executed: 100,
                 memory_used_bytes: 1024,
                 storage_reads: 5,
-                storage_writes: 2,
+                storage_writes: host.writes().len() as u64,
                 paradots_spawned: 1,
                 cpu_time_ms: execution_time,
             }),
+            trace_id: String::new(),
         })
     }
 
@@ -147,3 +555,93 @@ impl DotExecutor {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deterministic_execution_rejects_non_deterministic_calls() {
+        let mut host = HostCalls::new("dot_pricing", true);
+        host.call(HostCall::Log).unwrap();
+        let error = host.call(HostCall::Clock).unwrap_err();
+        assert!(matches!(error, ExecutorError::NonDeterministic { call: HostCall::Clock, .. }));
+        assert_eq!(error.to_string(), "Dot dot_pricing is declared deterministic but made a non-deterministic clock host call");
+        assert_eq!(host.calls(), [HostCall::Log]);
+
+        let mut host = HostCalls::new("dot_clock", false);
+        host.call(HostCall::Random).unwrap();
+        host.call(HostCall::DatabaseWrite).unwrap();
+        assert_eq!(host.calls(), [HostCall::Random, HostCall::DatabaseWrite]);
+    }
+
+    #[tokio::test]
+    async fn test_await_input_is_only_served_to_interactive_non_deterministic_dots() {
+        let prompt = || InputPrompt {
+            message: "waiting".to_string(),
+            schema: vec![],
+        };
+
+        let mut host = HostCalls::new("dot_form", false);
+        let error = host.await_input(prompt()).await.unwrap_err();
+        assert!(matches!(error, ExecutorError::NotInteractive(_)));
+
+        let mut host = HostCalls::new("dot_form", true);
+        let error = host.await_input(prompt()).await.unwrap_err();
+        assert!(matches!(error, ExecutorError::NonDeterministic { call: HostCall::AwaitInput, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_execution_writes_are_committed_as_a_state_version() {
+        let dot = StoredDot {
+            info: crate::proto::vm_service::DotInfo {
+                dot_id: "dot_counter".to_string(),
+                ..Default::default()
+            },
+            version: 1,
+            source: String::new(),
+            bytecode: vec![],
+            abi: None,
+            architecture: VmArchitecture::Arch64,
+        };
+        let executor = DotExecutor::new();
+        let initial = executor.state_store().create_dot("dot_counter").unwrap();
+
+        let request = ExecuteDotRequest {
+            inputs: HashMap::from([("count".to_string(), b"1".to_vec())]),
+            ..Default::default()
+        };
+        let response = executor.execute(&dot, request).await.unwrap();
+        assert_eq!(response.metrics.unwrap().storage_writes, 1);
+
+        let page = executor.state_store().read("dot_counter", &StateQuery::default()).unwrap();
+        assert!(page.version > initial);
+        assert_eq!(page.entries.get("count").unwrap(), b"1");
+    }
+
+    #[tokio::test]
+    async fn test_executor_is_selected_by_the_dots_architecture() {
+        let dot = |architecture: VmArchitecture| StoredDot {
+            info: crate::proto::vm_service::DotInfo {
+                dot_id: "dot_sensor".to_string(),
+                ..Default::default()
+            },
+            version: 1,
+            source: String::new(),
+            bytecode: vec![],
+            abi: None,
+            architecture,
+        };
+        let executor = DotExecutor::new();
+
+        let native = executor.execute(&dot(VmArchitecture::Arch128), ExecuteDotRequest::default()).await.unwrap();
+        assert!(native.logs[0].message.ends_with("on arch128"));
+        // Narrower bytecode runs on a wider executor in compatibility mode
+        let widened = executor.execute(&dot(VmArchitecture::Arch32), ExecuteDotRequest::default()).await.unwrap();
+        assert!(widened.logs[0].message.ends_with("on arch64"));
+
+        let error = executor.execute(&dot(VmArchitecture::Arch256), ExecuteDotRequest::default()).await.unwrap_err();
+        assert!(matches!(error, ExecutorError::Architecture(ArchitectureError::Unsupported { .. })));
+        assert_eq!(error.to_string(), "Dot targets arch256, but this runtime executes at most arch128");
+    }
+}
@@ -0,0 +1,424 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Interactive dot execution
+//!
+//! The client opens the stream with a start message and the dot runs as a task of its own.
+//! When the dot makes the `await_input` host call the task sends an [`InputRequested`]
+//! prompt and waits for the input carrying the same sequence number. If none arrives within
+//! the session's input timeout the execution is cancelled, releasing its version lease.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures::{Stream, StreamExt};
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tonic::Status;
+use tracing::{info, warn};
+
+use crate::proto::vm_service::interactive_execution_response::ResponseType;
+use crate::proto::vm_service::{
+    AbiField, ExecuteDotRequest, ExecutionCompleted, ExecutionError, ExecutionInput, ExecutionStarted, ExecutionStopped, InputRequested, InteractiveExecutionRequest, InteractiveExecutionResponse,
+    StartInteractiveExecution, StopReason, interactive_execution_request,
+};
+
+use super::executor::{DotExecutor, ExecutorError};
+use super::registry::{DotRegistry, RegistryError};
+
+/// How long an input request waits when the start message does not set a timeout
+pub const DEFAULT_INPUT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Upper bound on the input timeout a client may ask for
+pub const MAX_INPUT_TIMEOUT: Duration = Duration::from_secs(3600);
+
+type ResponseSender = UnboundedSender<Result<InteractiveExecutionResponse, Status>>;
+
+/// What a dot asks its caller for in an `await_input` host call
+#[derive(Debug, Clone)]
+pub struct InputPrompt {
+    pub message: String,
+    /// Inputs the dot is waiting for
+    pub schema: Vec<AbiField>,
+}
+
+/// The input request an execution is blocked on
+struct AwaitedInput {
+    sequence_number: u64,
+    reply: oneshot::Sender<HashMap<String, Vec<u8>>>,
+}
+
+/// Input request outstanding in a session, shared by the stream and the execution
+#[derive(Clone, Default)]
+struct PendingInput(Arc<Mutex<Option<AwaitedInput>>>);
+
+impl PendingInput {
+    /// Hand `input` to the execution if it answers the outstanding request
+    fn deliver(&self, session_id: &str, input: ExecutionInput) -> Result<(), ExecutionError> {
+        let mut awaited = self.0.lock().unwrap();
+        match awaited.take() {
+            Some(pending) if pending.sequence_number == input.sequence_number => {
+                let _ = pending.reply.send(input.inputs);
+                Ok(())
+            }
+            Some(pending) => {
+                let expected = pending.sequence_number;
+                *awaited = Some(pending);
+                Err(execution_error(
+                    session_id,
+                    "UNEXPECTED_INPUT",
+                    &format!("Input {} does not answer the pending input request {expected}", input.sequence_number),
+                ))
+            }
+            None => Err(execution_error(session_id, "UNEXPECTED_INPUT", "The execution is not waiting for input")),
+        }
+    }
+}
+
+/// Execution side of a session, answering `await_input` host calls through the client stream
+pub struct InputPort {
+    session_id: String,
+    responses: ResponseSender,
+    pending: PendingInput,
+    timeout: Duration,
+    next_sequence: u64,
+}
+
+impl InputPort {
+    fn new(session_id: String, responses: ResponseSender, timeout: Duration) -> Self {
+        Self {
+            session_id,
+            responses,
+            pending: PendingInput::default(),
+            timeout,
+            next_sequence: 0,
+        }
+    }
+
+    /// Prompt the client and wait for its answer, failing once the input timeout expires
+    pub async fn await_input(&mut self, prompt: InputPrompt) -> Result<HashMap<String, Vec<u8>>, ExecutorError> {
+        self.next_sequence += 1;
+        let sequence_number = self.next_sequence;
+        let (reply, received) = oneshot::channel();
+        *self.pending.0.lock().unwrap() = Some(AwaitedInput { sequence_number, reply });
+
+        let expires_at = SystemTime::now() + self.timeout;
+        let _ = self.responses.send(Ok(response(ResponseType::InputRequested(InputRequested {
+            session_id: self.session_id.clone(),
+            sequence_number,
+            prompt: prompt.message,
+            schema: prompt.schema,
+            expires_at: expires_at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+        }))));
+
+        let result = tokio::time::timeout(self.timeout, received).await;
+        // A late input must not be taken as the answer to this request
+        self.pending.0.lock().unwrap().take();
+        match result {
+            Ok(Ok(inputs)) => Ok(inputs),
+            Ok(Err(_)) => Err(ExecutorError::SessionClosed),
+            Err(_) => Err(ExecutorError::InputTimeout(self.timeout)),
+        }
+    }
+}
+
+/// Stream-side state of a running interactive execution
+struct Session {
+    session_id: String,
+    pending: PendingInput,
+    execution: JoinHandle<()>,
+}
+
+impl Session {
+    fn deliver(&self, input: ExecutionInput) -> Result<(), ExecutionError> {
+        if input.session_id != self.session_id {
+            return Err(execution_error(&input.session_id, "UNKNOWN_SESSION", &format!("This stream is running session '{}'", self.session_id)));
+        }
+        self.pending.deliver(&self.session_id, input)
+    }
+}
+
+/// Serve one interactive stream until the client stops the execution or disconnects
+///
+/// Messages that do not fit the protocol get an error response and leave the stream open.
+pub fn serve<S>(registry: Arc<DotRegistry>, executor: Arc<DotExecutor>, mut requests: S) -> UnboundedReceiverStream<Result<InteractiveExecutionResponse, Status>>
+where
+    S: Stream<Item = Result<InteractiveExecutionRequest, Status>> + Send + Unpin + 'static,
+{
+    let (tx, rx) = unbounded_channel();
+
+    tokio::spawn(async move {
+        let mut session: Option<Session> = None;
+
+        while let Some(Ok(request)) = requests.next().await {
+            let error = match request.request_type {
+                Some(interactive_execution_request::RequestType::Start(start)) => match session.as_ref() {
+                    Some(active) if !active.execution.is_finished() => Some(execution_error(&active.session_id, "SESSION_ACTIVE", "This stream already has a running execution")),
+                    _ => match start_session(&registry, &executor, start, &tx) {
+                        Ok(started) => {
+                            session = Some(started);
+                            None
+                        }
+                        Err(error) => Some(error),
+                    },
+                },
+                Some(interactive_execution_request::RequestType::Input(input)) => match session.as_ref() {
+                    Some(active) => active.deliver(input).err(),
+                    None => Some(execution_error(&input.session_id, "NO_SESSION", "Start an execution before sending input")),
+                },
+                Some(interactive_execution_request::RequestType::Command(command)) => Some(execution_error(
+                    &command.session_id,
+                    "UNSUPPORTED",
+                    "Execution commands are not supported in interactive sessions; use LiveDotDebugging",
+                )),
+                Some(interactive_execution_request::RequestType::Stop(stop)) => {
+                    if let Some(active) = session.take() {
+                        active.execution.abort();
+                    }
+                    let _ = tx.send(Ok(stopped(&stop.session_id, StopReason::StopUserRequested)));
+                    break;
+                }
+                None => Some(execution_error("", "INVALID_REQUEST", "Request carries no message")),
+            };
+
+            if let Some(error) = error
+                && tx.send(Ok(response(ResponseType::Error(error)))).is_err()
+            {
+                break;
+            }
+        }
+
+        // Cancelling drops the execution's version lease
+        if let Some(active) = session {
+            info!("Interactive session {} ended", active.session_id);
+            active.execution.abort();
+        }
+    });
+
+    UnboundedReceiverStream::new(rx)
+}
+
+/// Pin the dot's active version and start executing it on a task of its own
+fn start_session(registry: &DotRegistry, executor: &Arc<DotExecutor>, start: StartInteractiveExecution, tx: &ResponseSender) -> Result<Session, ExecutionError> {
+    let session_id = match start.session_id.as_str() {
+        "" => uuid::Uuid::new_v4().to_string(),
+        id => id.to_string(),
+    };
+    if start.dot_id.is_empty() {
+        return Err(execution_error(&session_id, "INVALID_REQUEST", "dot_id cannot be empty"));
+    }
+
+    let lease = registry.begin_execution(&start.dot_id).map_err(|e| {
+        let code = match e {
+            RegistryError::DotNotFound(_) => "NOT_FOUND",
+            RegistryError::NoActiveVersion(_) => "NO_ACTIVE_VERSION",
+            _ => "INTERNAL",
+        };
+        execution_error(&session_id, code, &e.to_string())
+    })?;
+    let timeout = match start.input_timeout_seconds {
+        0 => DEFAULT_INPUT_TIMEOUT,
+        seconds => Duration::from_secs(seconds.into()).min(MAX_INPUT_TIMEOUT),
+    };
+
+    info!("Interactive session {} started for dot {}", session_id, start.dot_id);
+    let _ = tx.send(Ok(response(ResponseType::Started(ExecutionStarted {
+        session_id: session_id.clone(),
+        dot_id: start.dot_id.clone(),
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+    }))));
+
+    let mut input = InputPort::new(session_id.clone(), tx.clone(), timeout);
+    let pending = input.pending.clone();
+    let request = ExecuteDotRequest {
+        dot_id: start.dot_id,
+        inputs: start.initial_inputs,
+        ..Default::default()
+    };
+    let executor = executor.clone();
+    let responses = tx.clone();
+    let id = session_id.clone();
+    let execution = tokio::spawn(async move {
+        let result = executor.execute_interactive(&lease, request, &mut input).await;
+        drop(lease);
+
+        let response = match result {
+            Ok(result) => response(ResponseType::Completed(ExecutionCompleted {
+                session_id: id,
+                outputs: result.outputs,
+                metrics: result.metrics,
+                dot_version: result.dot_version,
+            })),
+            Err(ExecutorError::InputTimeout(timeout)) => {
+                warn!("Interactive session {} cancelled after waiting {:?} for input", id, timeout);
+                stopped(&id, StopReason::StopTimeout)
+            }
+            Err(e) => response(ResponseType::Error(execution_error(&id, "EXECUTION_FAILED", &e.to_string()))),
+        };
+        let _ = responses.send(Ok(response));
+    });
+
+    Ok(Session { session_id, pending, execution })
+}
+
+fn response(response_type: ResponseType) -> InteractiveExecutionResponse {
+    InteractiveExecutionResponse { response_type: Some(response_type) }
+}
+
+fn stopped(session_id: &str, reason: StopReason) -> InteractiveExecutionResponse {
+    response(ResponseType::Stopped(ExecutionStopped {
+        session_id: session_id.to_string(),
+        reason: reason as i32,
+        final_metrics: None,
+    }))
+}
+
+fn execution_error(session_id: &str, error_code: &str, error_message: &str) -> ExecutionError {
+    ExecutionError {
+        session_id: session_id.to_string(),
+        error_code: error_code.to_string(),
+        error_message: error_message.to_string(),
+        stack_trace: String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::vm_service::{DeployDotRequest, DotAbi, DotInfo, ExecutionCommand, ListDotsRequest, StopExecution};
+    use crate::services::dots::registry::StoredDot;
+    use dotvm_core::bytecode::VmArchitecture;
+    use tokio::sync::mpsc::UnboundedReceiver;
+
+    fn approval_dot() -> StoredDot {
+        StoredDot {
+            info: DotInfo {
+                dot_id: "approval".to_string(),
+                ..Default::default()
+            },
+            version: 1,
+            source: String::new(),
+            bytecode: vec![],
+            abi: Some(DotAbi {
+                inputs: vec![AbiField {
+                    name: "approved".to_string(),
+                    required: true,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }),
+            architecture: VmArchitecture::Arch64,
+        }
+    }
+
+    fn input(session_id: &str, sequence_number: u64, pairs: &[(&str, &[u8])]) -> ExecutionInput {
+        ExecutionInput {
+            session_id: session_id.to_string(),
+            inputs: pairs.iter().map(|(k, v)| (k.to_string(), v.to_vec())).collect(),
+            sequence_number,
+        }
+    }
+
+    async fn next(rx: &mut UnboundedReceiver<Result<InteractiveExecutionResponse, Status>>) -> ResponseType {
+        rx.recv().await.unwrap().unwrap().response_type.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_execution_resumes_with_requested_input() {
+        let (tx, mut rx) = unbounded_channel();
+        let mut port = InputPort::new("session".to_string(), tx, Duration::from_secs(5));
+        let pending = port.pending.clone();
+        let execution = tokio::spawn(async move { DotExecutor::new().execute_interactive(&approval_dot(), ExecuteDotRequest::default(), &mut port).await });
+
+        let ResponseType::InputRequested(requested) = next(&mut rx).await else {
+            panic!("Expected an input request");
+        };
+        assert_eq!(requested.sequence_number, 1);
+        assert_eq!(requested.schema[0].name, "approved");
+
+        // Only the input answering the outstanding request resumes the execution
+        assert!(pending.deliver("session", input("session", 2, &[("approved", b"true")])).is_err());
+        pending.deliver("session", input("session", 1, &[("approved", b"true")])).unwrap();
+
+        let result = execution.await.unwrap().unwrap();
+        assert_eq!(result.outputs["approved"], b"true".to_vec());
+        assert!(result.metrics.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_input_timeout_cancels_execution() {
+        let (tx, _rx) = unbounded_channel();
+        let mut port = InputPort::new("session".to_string(), tx, Duration::from_millis(20));
+        let pending = port.pending.clone();
+
+        let result = DotExecutor::new().execute_interactive(&approval_dot(), ExecuteDotRequest::default(), &mut port).await;
+        assert!(matches!(result, Err(ExecutorError::InputTimeout(_))));
+
+        // The expired request no longer accepts an answer
+        assert!(pending.deliver("session", input("session", 1, &[("approved", b"true")])).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_protocol_errors_keep_the_stream_open() {
+        let registry = Arc::new(DotRegistry::new());
+        let deployed = registry
+            .deploy_dot(DeployDotRequest {
+                dot_name: "echo".to_string(),
+                dot_source: "dot echo {}".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let (requests, client) = unbounded_channel();
+        let mut rx = serve(registry.clone(), Arc::new(DotExecutor::new()), UnboundedReceiverStream::new(client));
+        let send = |request_type| requests.send(Ok(InteractiveExecutionRequest { request_type: Some(request_type) })).unwrap();
+        let mut next_response = async || rx.next().await.unwrap().unwrap().response_type.unwrap();
+
+        send(interactive_execution_request::RequestType::Input(input("session", 1, &[])));
+        assert!(matches!(next_response().await, ResponseType::Error(error) if error.error_code == "NO_SESSION"));
+        send(interactive_execution_request::RequestType::Command(ExecutionCommand::default()));
+        assert!(matches!(next_response().await, ResponseType::Error(error) if error.error_code == "UNSUPPORTED"));
+
+        send(interactive_execution_request::RequestType::Start(StartInteractiveExecution {
+            dot_id: deployed.dot_id.clone(),
+            session_id: "session".to_string(),
+            initial_inputs: HashMap::from([("x".to_string(), b"1".to_vec())]),
+            ..Default::default()
+        }));
+        assert!(matches!(next_response().await, ResponseType::Started(started) if started.session_id == "session"));
+        let ResponseType::Completed(completed) = next_response().await else {
+            panic!("Expected the execution to complete");
+        };
+        assert_eq!(completed.outputs["x"], b"1".to_vec());
+        assert_eq!(completed.dot_version, 1);
+
+        // The finished execution released its lease
+        let listed = registry.list_dots(ListDotsRequest::default()).await.unwrap();
+        assert_eq!(listed.dots[0].versions[0].in_flight_executions, 0);
+
+        send(interactive_execution_request::RequestType::Input(input("session", 1, &[])));
+        assert!(matches!(next_response().await, ResponseType::Error(error) if error.error_code == "UNEXPECTED_INPUT"));
+        send(interactive_execution_request::RequestType::Stop(StopExecution {
+            session_id: "session".to_string(),
+            force: false,
+        }));
+        assert!(matches!(next_response().await, ResponseType::Stopped(stopped) if stopped.reason == StopReason::StopUserRequested as i32));
+    }
+}
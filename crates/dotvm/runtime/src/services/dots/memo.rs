@@ -0,0 +1,421 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Result memoization for deterministic dots
+//!
+//! A dot whose ABI declares it deterministic computes its outputs from its
+//! inputs alone, so a successful execution is cached under the dot ID, the
+//! version that ran and a hash of the canonicalized inputs. Later executions
+//! with the same key are answered from the cache without running the dot.
+//! Redeploying or deleting a dot drops its entries, and caching can be turned
+//! off per dot. The cache is bounded by the encoded size of the responses it
+//! holds and evicts the least recently used entry first.
+
+use prost::Message;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+
+use crate::proto::vm_service::ExecuteDotResponse;
+
+/// Memory the result cache may hold when not configured otherwise
+pub const DEFAULT_RESULT_CACHE_BYTES: usize = 64 * 1024 * 1024;
+
+/// Identifies one memoized execution
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub dot_id: String,
+    pub version: u32,
+    pub inputs_hash: [u8; 32],
+}
+
+impl CacheKey {
+    pub fn new(dot_id: &str, version: u32, inputs: &HashMap<String, Vec<u8>>) -> Self {
+        Self {
+            dot_id: dot_id.to_string(),
+            version,
+            inputs_hash: inputs_hash(inputs),
+        }
+    }
+}
+
+/// SHA-256 over the inputs in name order
+///
+/// Inputs that parse as JSON are hashed in a canonical form, with object keys
+/// sorted and insignificant whitespace dropped, so payloads that only differ
+/// in formatting share a cache entry.
+pub fn inputs_hash(inputs: &HashMap<String, Vec<u8>>) -> [u8; 32] {
+    let mut names: Vec<&String> = inputs.keys().collect();
+    names.sort();
+
+    let mut hasher = Sha256::new();
+    for name in names {
+        let raw = &inputs[name];
+        let value = match serde_json::from_slice::<Value>(raw) {
+            Ok(json) => {
+                let mut canonical = Vec::with_capacity(raw.len());
+                write_canonical(&json, &mut canonical);
+                canonical
+            }
+            Err(_) => raw.clone(),
+        };
+        // Length prefixes keep name and value boundaries unambiguous
+        hasher.update((name.len() as u64).to_le_bytes());
+        hasher.update(name.as_bytes());
+        hasher.update((value.len() as u64).to_le_bytes());
+        hasher.update(&value);
+    }
+    hasher.finalize().into()
+}
+
+fn write_canonical(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Array(items) => {
+            out.push(b'[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    out.push(b',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(b']');
+        }
+        Value::Object(fields) => {
+            let mut fields: Vec<(&String, &Value)> = fields.iter().collect();
+            fields.sort_by(|a, b| a.0.cmp(b.0));
+            out.push(b'{');
+            for (index, (key, item)) in fields.into_iter().enumerate() {
+                if index > 0 {
+                    out.push(b',');
+                }
+                serde_json::to_writer(&mut *out, key).expect("writing to a Vec cannot fail");
+                out.push(b':');
+                write_canonical(item, out);
+            }
+            out.push(b'}');
+        }
+        scalar => serde_json::to_writer(&mut *out, scalar).expect("writing to a Vec cannot fail"),
+    }
+}
+
+/// Cache activity of one dot
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DotCacheStats {
+    pub dot_id: String,
+    pub enabled: bool,
+    pub hits: u64,
+    pub misses: u64,
+    /// Executions that asked to skip the cache
+    pub bypasses: u64,
+    pub entries: usize,
+    pub bytes: usize,
+}
+
+impl DotCacheStats {
+    /// Share of cache lookups answered from the cache
+    pub fn hit_rate(&self) -> f64 {
+        hit_rate(self.hits, self.misses)
+    }
+}
+
+/// Totals across the whole cache
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResultCacheStats {
+    pub capacity_bytes: usize,
+    pub bytes: usize,
+    pub entries: usize,
+    pub evictions: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub dots: Vec<DotCacheStats>,
+}
+
+impl ResultCacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        hit_rate(self.hits, self.misses)
+    }
+}
+
+fn hit_rate(hits: u64, misses: u64) -> f64 {
+    if hits + misses == 0 { 0.0 } else { hits as f64 / (hits + misses) as f64 }
+}
+
+struct Entry {
+    response: ExecuteDotResponse,
+    size: usize,
+    /// Position in [`CacheState::recency`]
+    tick: u64,
+}
+
+#[derive(Default)]
+struct DotCounters {
+    hits: u64,
+    misses: u64,
+    bypasses: u64,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<CacheKey, Entry>,
+    /// Keys ordered from least to most recently used
+    recency: BTreeMap<u64, CacheKey>,
+    next_tick: u64,
+    bytes: usize,
+    evictions: u64,
+    counters: HashMap<String, DotCounters>,
+    disabled: HashSet<String>,
+}
+
+impl CacheState {
+    fn touch(&mut self, key: &CacheKey) {
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.recency.remove(&entry.tick);
+            entry.tick = tick;
+            self.recency.insert(tick, key.clone());
+        }
+    }
+
+    fn remove(&mut self, key: &CacheKey) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        self.recency.remove(&entry.tick);
+        self.bytes -= entry.size;
+        Some(entry)
+    }
+
+    fn remove_dot(&mut self, dot_id: &str) -> usize {
+        let keys: Vec<CacheKey> = self.entries.keys().filter(|key| key.dot_id == dot_id).cloned().collect();
+        for key in &keys {
+            self.remove(key);
+        }
+        keys.len()
+    }
+}
+
+/// Bounded LRU cache of execution results
+pub struct ResultCache {
+    capacity_bytes: usize,
+    state: Mutex<CacheState>,
+}
+
+impl Default for ResultCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_RESULT_CACHE_BYTES)
+    }
+}
+
+impl ResultCache {
+    pub fn new(capacity_bytes: usize) -> Self {
+        Self {
+            capacity_bytes,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// Caching is on for every dot until it is disabled
+    pub fn is_enabled(&self, dot_id: &str) -> bool {
+        !self.state.lock().unwrap().disabled.contains(dot_id)
+    }
+
+    /// Turn caching on or off for a dot; disabling drops its entries and returns how many
+    pub fn set_enabled(&self, dot_id: &str, enabled: bool) -> usize {
+        let mut state = self.state.lock().unwrap();
+        state.counters.entry(dot_id.to_string()).or_default();
+        if enabled {
+            state.disabled.remove(dot_id);
+            0
+        } else {
+            state.disabled.insert(dot_id.to_string());
+            state.remove_dot(dot_id)
+        }
+    }
+
+    /// Cached response for `key`, counted as a hit or a miss
+    pub fn get(&self, key: &CacheKey) -> Option<ExecuteDotResponse> {
+        let mut state = self.state.lock().unwrap();
+        let response = state.entries.get(key).map(|entry| entry.response.clone());
+        let counters = state.counters.entry(key.dot_id.clone()).or_default();
+        if response.is_some() {
+            counters.hits += 1;
+            state.touch(key);
+        } else {
+            counters.misses += 1;
+        }
+        response
+    }
+
+    /// Count an execution that skipped the cache on request
+    pub fn record_bypass(&self, dot_id: &str) {
+        self.state.lock().unwrap().counters.entry(dot_id.to_string()).or_default().bypasses += 1;
+    }
+
+    /// Cache a response, evicting the least recently used entries to make room
+    ///
+    /// Responses larger than the whole cache, and responses of dots with caching
+    /// disabled, are not stored.
+    pub fn insert(&self, key: CacheKey, response: ExecuteDotResponse) {
+        let size = response.encoded_len() + key.dot_id.len() + std::mem::size_of::<CacheKey>();
+        if size > self.capacity_bytes {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if state.disabled.contains(&key.dot_id) {
+            return;
+        }
+        state.remove(&key);
+        while state.bytes + size > self.capacity_bytes {
+            let Some((_, oldest)) = state.recency.pop_first() else { break };
+            if let Some(entry) = state.entries.remove(&oldest) {
+                state.bytes -= entry.size;
+                state.evictions += 1;
+            }
+        }
+
+        let tick = state.next_tick;
+        state.next_tick += 1;
+        state.recency.insert(tick, key.clone());
+        state.bytes += size;
+        state.entries.insert(key, Entry { response, size, tick });
+    }
+
+    /// Drop every cached result of a dot, returning how many were dropped
+    pub fn invalidate_dot(&self, dot_id: &str) -> usize {
+        self.state.lock().unwrap().remove_dot(dot_id)
+    }
+
+    /// Forget a deleted dot, including its counters and caching setting
+    pub fn remove_dot(&self, dot_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.remove_dot(dot_id);
+        state.counters.remove(dot_id);
+        state.disabled.remove(dot_id);
+    }
+
+    /// Cache totals, with per-dot figures for `dot_id` or for every dot with cache activity
+    pub fn stats(&self, dot_id: Option<&str>) -> ResultCacheStats {
+        let state = self.state.lock().unwrap();
+
+        let mut dots: BTreeMap<&str, DotCacheStats> = BTreeMap::new();
+        for (id, counters) in &state.counters {
+            dots.insert(
+                id,
+                DotCacheStats {
+                    dot_id: id.clone(),
+                    enabled: !state.disabled.contains(id),
+                    hits: counters.hits,
+                    misses: counters.misses,
+                    bypasses: counters.bypasses,
+                    ..DotCacheStats::default()
+                },
+            );
+        }
+        for (key, entry) in &state.entries {
+            if let Some(stats) = dots.get_mut(key.dot_id.as_str()) {
+                stats.entries += 1;
+                stats.bytes += entry.size;
+            }
+        }
+
+        let (hits, misses) = state.counters.values().fold((0, 0), |(hits, misses), counters| (hits + counters.hits, misses + counters.misses));
+        ResultCacheStats {
+            capacity_bytes: self.capacity_bytes,
+            bytes: state.bytes,
+            entries: state.entries.len(),
+            evictions: state.evictions,
+            hits,
+            misses,
+            dots: dots.into_values().filter(|stats| dot_id.is_none_or(|id| stats.dot_id == id)).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs(pairs: &[(&str, &str)]) -> HashMap<String, Vec<u8>> {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.as_bytes().to_vec())).collect()
+    }
+
+    fn response(output: &str) -> ExecuteDotResponse {
+        ExecuteDotResponse {
+            success: true,
+            outputs: HashMap::from([("result".to_string(), output.as_bytes().to_vec())]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_inputs_hash_ignores_json_formatting() {
+        let compact = inputs(&[("order", r#"{"qty":2,"sku":"a"}"#), ("region", "eu")]);
+        let spaced = inputs(&[("region", "eu"), ("order", r#"{ "sku": "a", "qty": 2 }"#)]);
+        assert_eq!(inputs_hash(&compact), inputs_hash(&spaced));
+
+        let different = inputs(&[("order", r#"{"qty":3,"sku":"a"}"#), ("region", "eu")]);
+        assert_ne!(inputs_hash(&compact), inputs_hash(&different));
+        // Moving bytes between names changes the hash
+        assert_ne!(inputs_hash(&inputs(&[("ab", "c")])), inputs_hash(&inputs(&[("a", "bc")])));
+    }
+
+    #[test]
+    fn test_hits_misses_and_invalidation() {
+        let cache = ResultCache::default();
+        let key = CacheKey::new("dot_price", 1, &inputs(&[("qty", "2")]));
+
+        assert_eq!(cache.get(&key), None);
+        cache.insert(key.clone(), response("10"));
+        assert_eq!(cache.get(&key), Some(response("10")));
+        assert_eq!(cache.get(&CacheKey::new("dot_price", 2, &inputs(&[("qty", "2")]))), None);
+
+        let stats = cache.stats(Some("dot_price"));
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 2, 1));
+        assert!((stats.dots[0].hit_rate() - 1.0 / 3.0).abs() < f64::EPSILON);
+
+        assert_eq!(cache.invalidate_dot("dot_price"), 1);
+        assert_eq!(cache.get(&key), None);
+
+        // Disabled dots neither keep nor accept entries
+        cache.insert(key.clone(), response("10"));
+        assert_eq!(cache.set_enabled("dot_price", false), 1);
+        cache.insert(key.clone(), response("10"));
+        assert_eq!(cache.stats(None).entries, 0);
+        assert!(!cache.stats(None).dots[0].enabled);
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let first = CacheKey::new("dot_a", 1, &inputs(&[("n", "1")]));
+        let second = CacheKey::new("dot_a", 1, &inputs(&[("n", "2")]));
+        let third = CacheKey::new("dot_a", 1, &inputs(&[("n", "3")]));
+        let entry_size = response("x").encoded_len() + "dot_a".len() + std::mem::size_of::<CacheKey>();
+        let cache = ResultCache::new(entry_size * 2);
+
+        cache.insert(first.clone(), response("x"));
+        cache.insert(second.clone(), response("x"));
+        // Reading the first entry makes the second the least recently used
+        assert!(cache.get(&first).is_some());
+        cache.insert(third.clone(), response("x"));
+
+        assert!(cache.get(&first).is_some());
+        assert!(cache.get(&second).is_none());
+        assert!(cache.get(&third).is_some());
+        let stats = cache.stats(None);
+        assert_eq!((stats.entries, stats.bytes, stats.evictions), (2, entry_size * 2, 1));
+    }
+}
@@ -16,9 +16,19 @@
 
 //! Dots service - handles dot deployment, execution, and management
 
+pub mod architecture;
+pub mod checkpoints;
+pub mod debugger;
+pub mod dependencies;
 pub mod executor;
+pub mod interactive;
+pub mod memo;
 mod paradots;
+pub mod permissions;
+pub mod recording;
 pub mod registry;
 pub mod service; // Private - ParaDots are internal helpers
+pub mod state;
+pub mod upload;
 
 pub use service::DotsService;
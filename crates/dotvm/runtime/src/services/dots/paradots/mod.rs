@@ -14,10 +14,12 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-//! ParaDot management - internal components for automatic ParaDot coordination
+//! ParaDot management - automatic ParaDot coordination and the lifecycle of spawned ParaDots
 
 pub mod manager;
 pub mod registry;
+pub mod supervisor;
 
 pub(super) use manager::ParaDotManager;
 pub(super) use registry::ParaDotRegistry;
+pub(super) use supervisor::{ParaDotError, ParaDotQuery, ParaDotSource, ParaDotSpec, ParaDotSupervisor};
@@ -0,0 +1,619 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! ParaDot supervisor - lifecycle of ParaDots spawned on request under a parent dot
+//!
+//! Every ParaDot belongs to the dot that spawned it. Its limits are inherited from
+//! the parent's quota or capped by it, and each of its executions is allocated
+//! against the parent's quota, so a dot cannot get around its quota by handing
+//! work to helpers. A ParaDot's limits are shared evenly by its concurrent
+//! executions: one running at full concurrency holds exactly its limits.
+//!
+//! Terminating a ParaDot either drains it, refusing new executions and waiting
+//! for the running ones, or kills it, cancelling them. Deleting the parent kills
+//! all of its ParaDots.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::{Notify, watch};
+use tracing::{info, warn};
+
+use dotvm_core::vm::execution_controller::{ExecutionError, QuotaConfig, QuotaResource, ResourceRequirements, Task, TaskPriority, resource_allocation::ResourceAllocator};
+
+use super::registry::ParaDotRegistry;
+use crate::proto::vm_service::{DotQuota, ParaDotInfo, ParaDotMetadata, ParaDotState, ParaDotStats, ResourceRequirements as ParaDotRequirements};
+
+/// Allocator task ids of ParaDot executions start here, clear of the ids of dot tasks
+const EXECUTION_TASK_IDS: u64 = 1 << 62;
+
+/// Type reported for ParaDots spawned from bytecode
+const BYTECODE_TYPE: &str = "Bytecode";
+
+#[derive(Error, Debug)]
+pub enum ParaDotError {
+    #[error("ParaDot not found: {0}")]
+    NotFound(String),
+    #[error("Unknown ParaDot template: {0}")]
+    UnknownTemplate(String),
+    #[error("ParaDot {0} is terminating")]
+    Terminating(String),
+    #[error("ParaDot {paradot_id} is already running its limit of {limit} executions")]
+    ConcurrencyLimit { paradot_id: String, limit: usize },
+    #[error("Dot {dot_id} has no {resource} quota left for ParaDot {paradot_id}")]
+    QuotaExceeded { paradot_id: String, dot_id: String, resource: QuotaResource },
+    #[error("Resources for ParaDot {0} could not be allocated")]
+    AllocationFailed(String),
+    #[error("Execution of ParaDot {0} was cancelled")]
+    Cancelled(String),
+    #[error("Execution of ParaDot {paradot_id} timed out after {timeout:?}")]
+    Timeout { paradot_id: String, timeout: Duration },
+}
+
+/// What a ParaDot runs
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParaDotSource {
+    /// A built-in ParaDot type from the registry
+    Template(String),
+    Bytecode(Vec<u8>),
+}
+
+/// A ParaDot to spawn
+#[derive(Debug, Clone)]
+pub struct ParaDotSpec {
+    pub parent_dot_id: String,
+    pub name: String,
+    pub source: ParaDotSource,
+    /// Requested limits; zero fields are inherited from the parent's quota
+    pub limits: QuotaConfig,
+}
+
+/// Which ParaDots to list
+#[derive(Debug, Clone, Default)]
+pub struct ParaDotQuery {
+    pub parent_dot_id: Option<String>,
+    pub paradot_type: Option<String>,
+    /// Capabilities a ParaDot must all have
+    pub capabilities: Vec<String>,
+    /// Substring the name must contain
+    pub name_pattern: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct Activity {
+    terminating: bool,
+    active: usize,
+    executions: u64,
+    errors: u32,
+    total_execution_ms: u64,
+    last_used_at: u64,
+}
+
+struct SpawnedParaDot {
+    id: String,
+    name: String,
+    parent_dot_id: String,
+    paradot_type: String,
+    metadata: Option<ParaDotMetadata>,
+    source: ParaDotSource,
+    limits: QuotaConfig,
+    spawned_at: u64,
+    activity: Mutex<Activity>,
+    /// Woken whenever the last running execution finishes
+    drained: Notify,
+    /// Set once to cancel every running execution
+    killed: watch::Sender<bool>,
+}
+
+impl SpawnedParaDot {
+    fn to_info(&self) -> ParaDotInfo {
+        let activity = self.activity.lock().unwrap();
+        ParaDotInfo {
+            paradot_id: self.id.clone(),
+            name: self.name.clone(),
+            paradot_type: self.paradot_type.clone(),
+            metadata: self.metadata.clone(),
+            stats: Some(ParaDotStats {
+                execution_count: activity.executions,
+                average_execution_time_ms: if activity.executions == 0 {
+                    0.0
+                } else {
+                    activity.total_execution_ms as f64 / activity.executions as f64
+                },
+                error_count: activity.errors,
+                last_used_at: activity.last_used_at,
+            }),
+            parent_dot_id: self.parent_dot_id.clone(),
+            state: if activity.terminating { ParaDotState::Terminating } else { ParaDotState::Running } as i32,
+            limits: Some(DotQuota {
+                max_memory_mb: self.limits.max_memory_mb as u64,
+                max_cpu_cores: self.limits.max_cpu_cores,
+                max_concurrent_tasks: self.limits.max_concurrent_tasks as u32,
+            }),
+            spawned_at: self.spawned_at,
+            active_executions: activity.active as u32,
+        }
+    }
+
+    fn matches(&self, query: &ParaDotQuery) -> bool {
+        let capabilities = self.metadata.as_ref().map(|metadata| metadata.capabilities.as_slice()).unwrap_or_default();
+        query.parent_dot_id.as_ref().is_none_or(|parent| *parent == self.parent_dot_id)
+            && query.paradot_type.as_ref().is_none_or(|paradot_type| *paradot_type == self.paradot_type)
+            && query.name_pattern.as_ref().is_none_or(|pattern| self.name.contains(pattern.as_str()))
+            && query.capabilities.iter().all(|capability| capabilities.contains(capability))
+    }
+
+    /// Resources one execution is charged to the parent's quota
+    fn execution_requirements(&self) -> ResourceRequirements {
+        let share = self.limits.max_concurrent_tasks.max(1);
+        ResourceRequirements {
+            memory_mb: self.limits.max_memory_mb / share,
+            cpu_cores: self.limits.max_cpu_cores / share as f32,
+        }
+    }
+}
+
+/// Gives back what a running execution holds, also when the execution is dropped midway
+struct ExecutionGuard<'a> {
+    paradot: &'a SpawnedParaDot,
+    allocator: &'a ResourceAllocator,
+    task_id: Option<u64>,
+}
+
+impl Drop for ExecutionGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(task_id) = self.task_id {
+            self.allocator.release(task_id);
+        }
+        let mut activity = self.paradot.activity.lock().unwrap();
+        activity.active -= 1;
+        if activity.active == 0 {
+            self.paradot.drained.notify_waiters();
+        }
+    }
+}
+
+/// Tracks ParaDots spawned under dots and charges their executions to the parents' quotas
+pub struct ParaDotSupervisor {
+    templates: Arc<ParaDotRegistry>,
+    resource_allocator: Arc<ResourceAllocator>,
+    paradots: RwLock<HashMap<String, Arc<SpawnedParaDot>>>,
+    next_task_id: AtomicU64,
+}
+
+impl ParaDotSupervisor {
+    pub fn new(resource_allocator: Arc<ResourceAllocator>) -> Self {
+        Self {
+            templates: Arc::new(ParaDotRegistry::new()),
+            resource_allocator,
+            paradots: RwLock::new(HashMap::new()),
+            next_task_id: AtomicU64::new(EXECUTION_TASK_IDS),
+        }
+    }
+
+    /// Spawn a ParaDot under `spec.parent_dot_id`, whose existence the caller has checked
+    pub fn spawn(&self, spec: ParaDotSpec) -> Result<ParaDotInfo, ParaDotError> {
+        let (paradot_type, metadata) = match &spec.source {
+            ParaDotSource::Template(template) => {
+                let info = self.templates.get_paradot_type(template).map_err(|_| ParaDotError::UnknownTemplate(template.clone()))?;
+                let metadata = ParaDotMetadata {
+                    version: String::new(),
+                    description: info.description,
+                    capabilities: info.capabilities,
+                    resource_requirements: Some(ParaDotRequirements {
+                        max_memory_bytes: info.resource_requirements.max_memory_mb * 1024 * 1024,
+                        max_cpu_percent: info.resource_requirements.max_cpu_percent,
+                        max_execution_time_ms: 0,
+                        requires_network: info.resource_requirements.requires_network,
+                    }),
+                };
+                (info.name, Some(metadata))
+            }
+            ParaDotSource::Bytecode(_) => (BYTECODE_TYPE.to_string(), None),
+        };
+
+        let parent_quota = self.resource_allocator.quota(&spec.parent_dot_id).map(|status| status.config).unwrap_or_default();
+        let id = format!("paradot_{}", &uuid::Uuid::new_v4().simple().to_string()[..12]);
+        let name = if spec.name.is_empty() { paradot_type.clone() } else { spec.name };
+        let paradot = Arc::new(SpawnedParaDot {
+            id: id.clone(),
+            name,
+            parent_dot_id: spec.parent_dot_id,
+            paradot_type,
+            metadata,
+            source: spec.source,
+            limits: capped_limits(&spec.limits, &parent_quota),
+            spawned_at: chrono::Utc::now().timestamp() as u64,
+            activity: Mutex::new(Activity::default()),
+            drained: Notify::new(),
+            killed: watch::channel(false).0,
+        });
+
+        info!("Spawned ParaDot {} ({}) under dot {}", paradot.id, paradot.paradot_type, paradot.parent_dot_id);
+        let info = paradot.to_info();
+        self.paradots.write().unwrap().insert(id, paradot);
+        Ok(info)
+    }
+
+    /// ParaDots matching `query`, grouped by parent in spawn order
+    pub fn list(&self, query: &ParaDotQuery) -> Vec<ParaDotInfo> {
+        let paradots = self.paradots.read().unwrap();
+        let mut matching: Vec<&Arc<SpawnedParaDot>> = paradots.values().filter(|paradot| paradot.matches(query)).collect();
+        matching.sort_by(|a, b| (&a.parent_dot_id, a.spawned_at, &a.id).cmp(&(&b.parent_dot_id, b.spawned_at, &b.id)));
+        matching.into_iter().map(|paradot| paradot.to_info()).collect()
+    }
+
+    pub fn get(&self, paradot_id: &str) -> Option<ParaDotInfo> {
+        self.paradots.read().unwrap().get(paradot_id).map(|paradot| paradot.to_info())
+    }
+
+    /// Run `work` as an execution of the ParaDot, charged to its parent's quota
+    ///
+    /// The execution is refused while the ParaDot is terminating, at its concurrency
+    /// limit or when the parent's quota has no room left, and is cancelled if the
+    /// ParaDot is killed or `timeout` elapses first.
+    pub async fn execute<F, T>(&self, paradot_id: &str, timeout: Option<Duration>, work: impl FnOnce(&ParaDotSource) -> F) -> Result<T, ParaDotError>
+    where
+        F: Future<Output = T>,
+    {
+        let paradot = self.paradots.read().unwrap().get(paradot_id).cloned().ok_or_else(|| ParaDotError::NotFound(paradot_id.to_string()))?;
+
+        {
+            let mut activity = paradot.activity.lock().unwrap();
+            if activity.terminating {
+                return Err(ParaDotError::Terminating(paradot.id.clone()));
+            }
+            if activity.active >= paradot.limits.max_concurrent_tasks {
+                return Err(ParaDotError::ConcurrencyLimit {
+                    paradot_id: paradot.id.clone(),
+                    limit: paradot.limits.max_concurrent_tasks,
+                });
+            }
+            activity.active += 1;
+        }
+        let mut guard = ExecutionGuard {
+            paradot: &paradot,
+            allocator: &self.resource_allocator,
+            task_id: None,
+        };
+
+        let task = Task {
+            id: self.next_task_id.fetch_add(1, Ordering::Relaxed),
+            priority: TaskPriority::default(),
+            resource_requirements: paradot.execution_requirements(),
+            dot_id: Some(paradot.parent_dot_id.clone()),
+        };
+        self.resource_allocator.allocate_resources(&task).await.map_err(|e| match e {
+            ExecutionError::QuotaExceeded { dot_id, resource } => ParaDotError::QuotaExceeded {
+                paradot_id: paradot.id.clone(),
+                dot_id,
+                resource,
+            },
+            _ => ParaDotError::AllocationFailed(paradot.id.clone()),
+        })?;
+        guard.task_id = Some(task.id);
+
+        let started = Instant::now();
+        let mut killed = paradot.killed.subscribe();
+        let run = async {
+            tokio::select! {
+                output = work(&paradot.source) => Ok(output),
+                _ = killed.wait_for(|killed| *killed) => Err(ParaDotError::Cancelled(paradot.id.clone())),
+            }
+        };
+        let result = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, run).await.unwrap_or_else(|_| {
+                Err(ParaDotError::Timeout {
+                    paradot_id: paradot.id.clone(),
+                    timeout,
+                })
+            }),
+            None => run.await,
+        };
+
+        let mut activity = paradot.activity.lock().unwrap();
+        activity.executions += 1;
+        activity.total_execution_ms += started.elapsed().as_millis() as u64;
+        activity.last_used_at = chrono::Utc::now().timestamp() as u64;
+        if result.is_err() {
+            activity.errors += 1;
+        }
+        drop(activity);
+        drop(guard);
+
+        result
+    }
+
+    /// Stop a ParaDot and remove it once none of its executions are running
+    ///
+    /// A graceful termination lets running executions finish; otherwise they are
+    /// cancelled. Returns how many executions were cancelled.
+    pub async fn terminate(&self, paradot_id: &str, graceful: bool) -> Result<usize, ParaDotError> {
+        let paradot = self.paradots.read().unwrap().get(paradot_id).cloned().ok_or_else(|| ParaDotError::NotFound(paradot_id.to_string()))?;
+
+        let cancelled = {
+            let mut activity = paradot.activity.lock().unwrap();
+            activity.terminating = true;
+            if graceful { 0 } else { activity.active }
+        };
+        if !graceful {
+            paradot.killed.send_replace(true);
+        }
+
+        loop {
+            let drained = paradot.drained.notified();
+            if paradot.activity.lock().unwrap().active == 0 {
+                break;
+            }
+            drained.await;
+        }
+
+        self.paradots.write().unwrap().remove(paradot_id);
+        info!("Terminated ParaDot {} of dot {} ({} executions cancelled)", paradot.id, paradot.parent_dot_id, cancelled);
+        Ok(cancelled)
+    }
+
+    /// Kill every ParaDot of a dot that is going away, returning how many there were
+    pub async fn terminate_children(&self, parent_dot_id: &str) -> usize {
+        let children: Vec<String> = self
+            .paradots
+            .read()
+            .unwrap()
+            .values()
+            .filter(|paradot| paradot.parent_dot_id == parent_dot_id)
+            .map(|paradot| paradot.id.clone())
+            .collect();
+
+        for paradot_id in &children {
+            if let Err(e) = self.terminate(paradot_id, false).await {
+                warn!("Failed to clean up ParaDot {} of dot {}: {}", paradot_id, parent_dot_id, e);
+            }
+        }
+        children.len()
+    }
+}
+
+/// Requested limits with zero fields taken from the parent's quota and the rest capped by it
+fn capped_limits(requested: &QuotaConfig, parent: &QuotaConfig) -> QuotaConfig {
+    let cap = |requested: usize, parent: usize| if requested == 0 { parent } else { requested.min(parent) };
+    QuotaConfig {
+        max_memory_mb: cap(requested.max_memory_mb, parent.max_memory_mb),
+        max_cpu_cores: if requested.max_cpu_cores.is_nan() || requested.max_cpu_cores <= 0.0 {
+            parent.max_cpu_cores
+        } else {
+            requested.max_cpu_cores.min(parent.max_cpu_cores)
+        },
+        max_concurrent_tasks: cap(requested.max_concurrent_tasks, parent.max_concurrent_tasks),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn supervisor() -> ParaDotSupervisor {
+        let allocator = Arc::new(ResourceAllocator::new());
+        allocator.set_quota(
+            "parent",
+            QuotaConfig {
+                max_memory_mb: 512,
+                max_cpu_cores: 2.0,
+                max_concurrent_tasks: 4,
+            },
+        );
+        ParaDotSupervisor::new(allocator)
+    }
+
+    fn spec(limits: QuotaConfig) -> ParaDotSpec {
+        ParaDotSpec {
+            parent_dot_id: "parent".to_string(),
+            name: String::new(),
+            source: ParaDotSource::Template("DataProcessor".to_string()),
+            limits,
+        }
+    }
+
+    fn unlimited() -> QuotaConfig {
+        QuotaConfig {
+            max_memory_mb: 0,
+            max_cpu_cores: 0.0,
+            max_concurrent_tasks: 0,
+        }
+    }
+
+    #[test]
+    fn test_limits_are_inherited_or_capped_by_the_parent() {
+        let supervisor = supervisor();
+
+        let inherited = supervisor.spawn(spec(unlimited())).unwrap();
+        assert_eq!(
+            inherited.limits,
+            Some(DotQuota {
+                max_memory_mb: 512,
+                max_cpu_cores: 2.0,
+                max_concurrent_tasks: 4
+            })
+        );
+        assert_eq!(inherited.name, "DataProcessor");
+        assert_eq!(inherited.parent_dot_id, "parent");
+
+        let capped = supervisor
+            .spawn(spec(QuotaConfig {
+                max_memory_mb: 4096,
+                max_cpu_cores: 0.5,
+                max_concurrent_tasks: 0,
+            }))
+            .unwrap();
+        assert_eq!(
+            capped.limits,
+            Some(DotQuota {
+                max_memory_mb: 512,
+                max_cpu_cores: 0.5,
+                max_concurrent_tasks: 4
+            })
+        );
+
+        let unknown = ParaDotSpec {
+            source: ParaDotSource::Template("Teleporter".to_string()),
+            ..spec(unlimited())
+        };
+        assert!(matches!(supervisor.spawn(unknown), Err(ParaDotError::UnknownTemplate(_))));
+    }
+
+    #[tokio::test]
+    async fn test_executions_are_charged_to_the_parent() {
+        let supervisor = supervisor();
+        let paradot = supervisor
+            .spawn(spec(QuotaConfig {
+                max_memory_mb: 400,
+                max_cpu_cores: 1.0,
+                max_concurrent_tasks: 2,
+            }))
+            .unwrap();
+
+        let usage = supervisor
+            .execute(&paradot.paradot_id, None, |_| async { supervisor.resource_allocator.quota("parent").unwrap().usage })
+            .await
+            .unwrap();
+        assert_eq!((usage.memory_mb, usage.active_tasks), (200, 1));
+
+        let after = supervisor.resource_allocator.quota("parent").unwrap().usage;
+        assert_eq!((after.memory_mb, after.active_tasks), (0, 0));
+        assert_eq!(supervisor.get(&paradot.paradot_id).unwrap().stats.unwrap().execution_count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_parent_quota_bounds_paradot_executions() {
+        let supervisor = Arc::new(supervisor());
+        let first = supervisor.spawn(spec(unlimited())).unwrap();
+        let second = supervisor.spawn(spec(unlimited())).unwrap();
+
+        // Each execution takes a quarter of the parent's quota, so four fill it
+        let (release, hold) = watch::channel(false);
+        let running: Vec<_> = (0..4)
+            .map(|i| {
+                let supervisor = supervisor.clone();
+                let paradot_id = if i % 2 == 0 { first.paradot_id.clone() } else { second.paradot_id.clone() };
+                let mut hold = hold.clone();
+                tokio::spawn(async move { supervisor.execute(&paradot_id, None, |_| async move { hold.wait_for(|released| *released).await.is_ok() }).await })
+            })
+            .collect();
+        while supervisor.resource_allocator.quota("parent").unwrap().usage.active_tasks < 4 {
+            tokio::task::yield_now().await;
+        }
+
+        let refused = supervisor.execute(&first.paradot_id, None, |_| async {}).await;
+        assert!(
+            matches!(
+                refused,
+                Err(ParaDotError::QuotaExceeded {
+                    resource: QuotaResource::ConcurrentTasks,
+                    ..
+                })
+            ),
+            "{refused:?}"
+        );
+
+        release.send_replace(true);
+        for execution in running {
+            assert!(execution.await.unwrap().unwrap());
+        }
+        assert_eq!(supervisor.resource_allocator.quota("parent").unwrap().usage.active_tasks, 0);
+    }
+
+    #[tokio::test]
+    async fn test_graceful_termination_drains_executions() {
+        let supervisor = Arc::new(supervisor());
+        let paradot = supervisor.spawn(spec(unlimited())).unwrap();
+
+        let (release, mut hold) = watch::channel(false);
+        let execution = {
+            let supervisor = supervisor.clone();
+            let paradot_id = paradot.paradot_id.clone();
+            tokio::spawn(async move { supervisor.execute(&paradot_id, None, |_| async move { hold.wait_for(|released| *released).await.is_ok() }).await })
+        };
+        while supervisor.get(&paradot.paradot_id).unwrap().active_executions == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        let termination = {
+            let supervisor = supervisor.clone();
+            let paradot_id = paradot.paradot_id.clone();
+            tokio::spawn(async move { supervisor.terminate(&paradot_id, true).await })
+        };
+        while supervisor.get(&paradot.paradot_id).unwrap().state != ParaDotState::Terminating as i32 {
+            tokio::task::yield_now().await;
+        }
+        assert!(matches!(supervisor.execute(&paradot.paradot_id, None, |_| async {}).await, Err(ParaDotError::Terminating(_))));
+
+        release.send_replace(true);
+        assert!(execution.await.unwrap().unwrap());
+        assert_eq!(termination.await.unwrap().unwrap(), 0);
+        assert!(supervisor.get(&paradot.paradot_id).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_killing_cancels_executions() {
+        let supervisor = Arc::new(supervisor());
+        let paradot = supervisor.spawn(spec(unlimited())).unwrap();
+
+        let execution = {
+            let supervisor = supervisor.clone();
+            let paradot_id = paradot.paradot_id.clone();
+            tokio::spawn(async move { supervisor.execute(&paradot_id, None, |_| std::future::pending::<()>()).await })
+        };
+        while supervisor.get(&paradot.paradot_id).unwrap().active_executions == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        assert_eq!(supervisor.terminate(&paradot.paradot_id, false).await.unwrap(), 1);
+        assert!(matches!(execution.await.unwrap(), Err(ParaDotError::Cancelled(_))));
+        assert_eq!(supervisor.resource_allocator.quota("parent").unwrap().usage.active_tasks, 0);
+        assert!(matches!(supervisor.terminate(&paradot.paradot_id, false).await, Err(ParaDotError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_list_filters() {
+        let supervisor = supervisor();
+        supervisor.spawn(spec(unlimited())).unwrap();
+        supervisor
+            .spawn(ParaDotSpec {
+                parent_dot_id: "other".to_string(),
+                name: "resizer".to_string(),
+                source: ParaDotSource::Bytecode(vec![0xD0, 0x7D]),
+                limits: unlimited(),
+            })
+            .unwrap();
+
+        assert_eq!(supervisor.list(&ParaDotQuery::default()).len(), 2);
+        let of_other = supervisor.list(&ParaDotQuery {
+            parent_dot_id: Some("other".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(of_other.len(), 1);
+        assert_eq!((of_other[0].name.as_str(), of_other[0].paradot_type.as_str()), ("resizer", BYTECODE_TYPE));
+
+        let filtering = supervisor.list(&ParaDotQuery {
+            capabilities: vec!["filtering".to_string()],
+            ..Default::default()
+        });
+        assert_eq!(filtering.len(), 1);
+        assert_eq!(filtering[0].parent_dot_id, "parent");
+    }
+}
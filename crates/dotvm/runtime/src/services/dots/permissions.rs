@@ -0,0 +1,319 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Execution permissions declared by a dot's ABI
+//!
+//! Every execution names an operation, or runs the dot's entry point as
+//! [`DEFAULT_OPERATION`], and is checked against the permissions of the dot's
+//! latest registered ABI. Public operations run for anyone. Protected operations
+//! need the principal the gateway authenticated, forwarded in [`PRINCIPAL_HEADER`]
+//! together with its roles, and owner operations need the principal that deployed
+//! the dot. Operations the permissions do not list are denied. Dots without a
+//! registered ABI, or whose ABI declares no permissions, are not access controlled.
+//!
+//! Permissions are cached per dot until the dot registers another ABI. Every
+//! denial is logged and kept in a bounded audit trail.
+
+use dotvm_common::telemetry::{PRINCIPAL_HEADER, ROLES_HEADER};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use thiserror::Error;
+use tonic::metadata::MetadataMap;
+use tracing::warn;
+
+use crate::proto::vm_service::PermissionConfig;
+use crate::services::abi::registry::{AbiRegistry, RegistryError};
+
+/// Operation an execution runs when it does not name one
+pub const DEFAULT_OPERATION: &str = "main";
+
+/// Denials kept in the audit trail; older ones are dropped first
+pub const DENIAL_AUDIT_CAPACITY: usize = 1024;
+
+/// Who an execution runs for, as forwarded by the gateway
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Caller {
+    /// Authenticated principal; `None` for anonymous callers
+    pub principal: Option<String>,
+    pub roles: Vec<String>,
+}
+
+impl Caller {
+    pub fn from_metadata(metadata: &MetadataMap) -> Self {
+        let header = |name: &str| metadata.get(name).and_then(|value| value.to_str().ok()).map(str::trim).filter(|value| !value.is_empty());
+        Self {
+            principal: header(PRINCIPAL_HEADER).map(str::to_string),
+            roles: header(ROLES_HEADER)
+                .into_iter()
+                .flat_map(|roles| roles.split(','))
+                .map(str::trim)
+                .filter(|role| !role.is_empty())
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+}
+
+/// Why an execution was denied
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum DenialReason {
+    #[error("the operation is not declared in the dot's ABI permissions")]
+    Undeclared,
+    #[error("the operation requires an authenticated caller")]
+    Unauthenticated,
+    #[error("the operation requires one of the roles {}", .0.join(", "))]
+    MissingRole(Vec<String>),
+    #[error("the operation is reserved for the dot's owner")]
+    NotOwner,
+}
+
+#[derive(Error, Debug)]
+pub enum PermissionError {
+    #[error("Permission denied for operation {operation} of dot {dot_id}: {reason}")]
+    Denied { dot_id: String, operation: String, reason: DenialReason },
+    #[error("Failed to read ABI permissions: {0}")]
+    Registry(#[from] RegistryError),
+}
+
+/// One denied execution in the audit trail
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermissionDenial {
+    pub principal: Option<String>,
+    pub dot_id: String,
+    pub operation: String,
+    pub reason: DenialReason,
+    /// Unix timestamp in seconds
+    pub denied_at: u64,
+}
+
+/// Checks executions against the permissions of the ABIs in an [`AbiRegistry`]
+pub struct PermissionEvaluator {
+    abis: Arc<AbiRegistry>,
+    /// Permissions of each dot's latest ABI, `None` for dots without any
+    cache: RwLock<HashMap<String, Option<Arc<PermissionConfig>>>>,
+    /// Bumped on every invalidation, so a lookup racing a registration is not cached
+    generation: AtomicU64,
+    denials: Mutex<VecDeque<PermissionDenial>>,
+}
+
+impl PermissionEvaluator {
+    /// Evaluates against `abis`, dropping a dot's cached permissions whenever it registers an ABI
+    pub fn new(abis: Arc<AbiRegistry>) -> Arc<Self> {
+        let evaluator = Arc::new(Self {
+            abis: abis.clone(),
+            cache: RwLock::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+            denials: Mutex::new(VecDeque::new()),
+        });
+        let weak = Arc::downgrade(&evaluator);
+        abis.on_registered(move |dot_id| {
+            if let Some(evaluator) = weak.upgrade() {
+                evaluator.invalidate(dot_id);
+            }
+        });
+        evaluator
+    }
+
+    /// Allow `caller` to run `operation` of a dot owned by `owner_id`, or record why not
+    pub fn check(&self, dot_id: &str, operation: &str, caller: &Caller, owner_id: &str) -> Result<(), PermissionError> {
+        let Some(permissions) = self.permissions(dot_id)? else {
+            return Ok(());
+        };
+        let Err(reason) = evaluate(&permissions, operation, caller, owner_id) else {
+            return Ok(());
+        };
+
+        let principal = caller.principal.as_deref().unwrap_or("anonymous");
+        warn!(dot_id, operation, principal, reason = %reason, "Denied execution");
+        let mut denials = self.denials.lock().unwrap_or_else(|e| e.into_inner());
+        if denials.len() == DENIAL_AUDIT_CAPACITY {
+            denials.pop_front();
+        }
+        denials.push_back(PermissionDenial {
+            principal: caller.principal.clone(),
+            dot_id: dot_id.to_string(),
+            operation: operation.to_string(),
+            reason: reason.clone(),
+            denied_at: chrono::Utc::now().timestamp() as u64,
+        });
+
+        Err(PermissionError::Denied {
+            dot_id: dot_id.to_string(),
+            operation: operation.to_string(),
+            reason,
+        })
+    }
+
+    /// Forget the cached permissions of a dot, e.g. once it is deleted
+    pub fn invalidate(&self, dot_id: &str) {
+        let mut cache = self.cache.write().unwrap_or_else(|e| e.into_inner());
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        cache.remove(dot_id);
+    }
+
+    /// Recorded denials, oldest first
+    pub fn denials(&self) -> Vec<PermissionDenial> {
+        self.denials.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect()
+    }
+
+    fn permissions(&self, dot_id: &str) -> Result<Option<Arc<PermissionConfig>>, RegistryError> {
+        if let Some(permissions) = self.cache.read().unwrap_or_else(|e| e.into_inner()).get(dot_id) {
+            return Ok(permissions.clone());
+        }
+
+        let generation = self.generation.load(Ordering::Acquire);
+        let permissions = self.abis.declared_permissions(dot_id)?.map(Arc::new);
+        let mut cache = self.cache.write().unwrap_or_else(|e| e.into_inner());
+        // A registration during the lookup may have made the result stale, so it is used once but not kept
+        if self.generation.load(Ordering::Acquire) == generation {
+            cache.insert(dot_id.to_string(), permissions.clone());
+        }
+        Ok(permissions)
+    }
+}
+
+/// The most restrictive listing of `operation` decides
+fn evaluate(permissions: &PermissionConfig, operation: &str, caller: &Caller, owner_id: &str) -> Result<(), DenialReason> {
+    if permissions.owner_operations.iter().any(|owned| owned == operation) {
+        return match &caller.principal {
+            Some(principal) if !owner_id.is_empty() && principal == owner_id => Ok(()),
+            Some(_) => Err(DenialReason::NotOwner),
+            None => Err(DenialReason::Unauthenticated),
+        };
+    }
+    if let Some(protected) = permissions.protected_operations.get(operation) {
+        if caller.principal.is_none() {
+            return Err(DenialReason::Unauthenticated);
+        }
+        if !protected.required_roles.is_empty() && !protected.required_roles.iter().any(|role| caller.roles.contains(role)) {
+            return Err(DenialReason::MissingRole(protected.required_roles.clone()));
+        }
+        return Ok(());
+    }
+    if permissions.public_operations.iter().any(|public| public == operation) {
+        return Ok(());
+    }
+    Err(DenialReason::Undeclared)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::vm_service::{DotAbi, OperationPermission, RegisterAbiRequest};
+
+    fn caller(principal: &str, roles: &[&str]) -> Caller {
+        Caller {
+            principal: Some(principal.to_string()),
+            roles: roles.iter().map(|role| role.to_string()).collect(),
+        }
+    }
+
+    fn permissions(public: &[&str], protected: &[(&str, &[&str])], owner: &[&str]) -> PermissionConfig {
+        PermissionConfig {
+            public_operations: public.iter().map(|operation| operation.to_string()).collect(),
+            protected_operations: protected
+                .iter()
+                .map(|(operation, roles)| {
+                    let permission = OperationPermission {
+                        required_roles: roles.iter().map(|role| role.to_string()).collect(),
+                        ..Default::default()
+                    };
+                    (operation.to_string(), permission)
+                })
+                .collect(),
+            owner_operations: owner.iter().map(|operation| operation.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    async fn register(abis: &AbiRegistry, version: &str, permissions: PermissionConfig) {
+        let abi = DotAbi {
+            dot_name: "wallet".to_string(),
+            version: version.to_string(),
+            description: "Wallet dot".to_string(),
+            permissions: Some(permissions),
+            ..Default::default()
+        };
+        let request = RegisterAbiRequest {
+            dot_id: "wallet".to_string(),
+            abi: Some(abi),
+            registrar_id: "tester".to_string(),
+        };
+        assert!(abis.register_abi(request).await.unwrap().success);
+    }
+
+    #[test]
+    fn test_operations_are_checked_by_their_listing() {
+        let permissions = permissions(&["balance", "freeze"], &[("transfer", &[]), ("audit", &["auditor", "admin"])], &["freeze"]);
+        let anonymous = Caller::default();
+
+        assert_eq!(evaluate(&permissions, "balance", &anonymous, "alice"), Ok(()));
+        assert_eq!(evaluate(&permissions, "transfer", &anonymous, "alice"), Err(DenialReason::Unauthenticated));
+        assert_eq!(evaluate(&permissions, "transfer", &caller("bob", &[]), "alice"), Ok(()));
+        assert_eq!(
+            evaluate(&permissions, "audit", &caller("bob", &["viewer"]), "alice"),
+            Err(DenialReason::MissingRole(vec!["auditor".to_string(), "admin".to_string()]))
+        );
+        assert_eq!(evaluate(&permissions, "audit", &caller("bob", &["admin"]), "alice"), Ok(()));
+        // Listing an operation as owner-only wins over listing it as public
+        assert_eq!(evaluate(&permissions, "freeze", &caller("bob", &["admin"]), "alice"), Err(DenialReason::NotOwner));
+        assert_eq!(evaluate(&permissions, "freeze", &caller("alice", &[]), "alice"), Ok(()));
+        assert_eq!(evaluate(&permissions, "freeze", &caller("alice", &[]), ""), Err(DenialReason::NotOwner));
+        assert_eq!(evaluate(&permissions, "mint", &caller("alice", &["admin"]), "alice"), Err(DenialReason::Undeclared));
+    }
+
+    #[test]
+    fn test_caller_is_read_from_forwarded_metadata() {
+        let mut metadata = MetadataMap::new();
+        assert_eq!(Caller::from_metadata(&metadata), Caller::default());
+
+        metadata.insert(PRINCIPAL_HEADER, "bob".parse().unwrap());
+        metadata.insert(ROLES_HEADER, "admin, auditor,,".parse().unwrap());
+        assert_eq!(Caller::from_metadata(&metadata), caller("bob", &["admin", "auditor"]));
+    }
+
+    #[tokio::test]
+    async fn test_reregistration_invalidates_cached_permissions() {
+        let abis = Arc::new(AbiRegistry::new());
+        let evaluator = PermissionEvaluator::new(abis.clone());
+        let anonymous = Caller::default();
+
+        // Without an ABI the dot is open
+        assert!(evaluator.check("wallet", "transfer", &anonymous, "alice").is_ok());
+
+        register(&abis, "1.0.0", permissions(&["transfer"], &[], &[])).await;
+        assert!(evaluator.check("wallet", "transfer", &anonymous, "alice").is_ok());
+
+        register(&abis, "1.1.0", permissions(&[], &[("transfer", &[])], &[])).await;
+        let denied = evaluator.check("wallet", "transfer", &anonymous, "alice").unwrap_err();
+        assert!(matches!(
+            denied,
+            PermissionError::Denied {
+                reason: DenialReason::Unauthenticated,
+                ..
+            }
+        ));
+        assert!(evaluator.check("wallet", "transfer", &caller("bob", &[]), "alice").is_ok());
+        assert!(evaluator.check("wallet", DEFAULT_OPERATION, &caller("bob", &[]), "alice").is_err());
+
+        let denials = evaluator.denials();
+        assert_eq!(denials.len(), 2);
+        assert_eq!((denials[0].principal.as_deref(), denials[0].operation.as_str()), (None, "transfer"));
+        assert_eq!((denials[1].principal.as_deref(), denials[1].reason.clone()), (Some("bob"), DenialReason::Undeclared));
+        assert!(denials.iter().all(|denial| denial.dot_id == "wallet"));
+    }
+}
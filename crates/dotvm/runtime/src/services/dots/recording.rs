@@ -0,0 +1,225 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Execution recording for deterministic replay
+//!
+//! Executions of the dots named in the configuration, and any execution whose request
+//! asks for it, are recorded: the bytecode runs under a recording host tape and the trace
+//! (inputs, dot and state versions, every host-call result, outputs and events) is kept
+//! in the trace collection, capped in size. `dotvm replay <trace-id>` re-runs it.
+//!
+//! Input fields named in the configuration are masked: their values never reach the trace,
+//! only their names do.
+
+use dotvm_core::bytecode::BytecodeFile;
+use dotvm_core::vm::replay::{DEFAULT_MAX_TRACE_BYTES, ExecutionTrace, RecordedExecution, TraceStore, TraceStoreError, TracedEvent, record, trace_executor};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::proto::vm_service::{ExecuteDotRequest, ExecuteDotResponse};
+
+use super::registry::StoredDot;
+
+/// Which executions are recorded and what their traces may hold
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordingConfig {
+    /// Dots every execution of which is recorded
+    pub dots: HashSet<String>,
+    /// Traces are cut down to this many serialized bytes
+    pub max_trace_bytes: usize,
+    /// Input fields whose values are left out of traces
+    pub masked_inputs: HashSet<String>,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            dots: HashSet::new(),
+            max_trace_bytes: DEFAULT_MAX_TRACE_BYTES,
+            masked_inputs: HashSet::new(),
+        }
+    }
+}
+
+/// Records executions into a [`TraceStore`]
+pub struct ExecutionRecorder {
+    config: RecordingConfig,
+    store: Arc<TraceStore>,
+}
+
+impl ExecutionRecorder {
+    pub fn new(store: Arc<TraceStore>, config: RecordingConfig) -> Self {
+        Self { config, store }
+    }
+
+    pub fn store(&self) -> &Arc<TraceStore> {
+        &self.store
+    }
+
+    pub fn config(&self) -> &RecordingConfig {
+        &self.config
+    }
+
+    /// Whether this execution of `dot_id` is recorded, by configuration or by request
+    pub fn should_record(&self, dot_id: &str, request: &ExecuteDotRequest) -> bool {
+        self.config.dots.contains(dot_id) || request.options.as_ref().is_some_and(|options| options.record_execution)
+    }
+
+    /// Run the dot's bytecode under a recording tape and keep the trace, returning its id
+    ///
+    /// Until every execution runs in the VM, recording runs the bytecode there alongside the
+    /// execution, the way a debugging session does, so the trace holds real host calls.
+    pub async fn record(&self, dot: &StoredDot, request: &ExecuteDotRequest, state_version: Option<u64>, response: &ExecuteDotResponse) -> Result<String, TraceStoreError> {
+        let dot_id = dot.info.dot_id.clone();
+        let bytecode = dot.bytecode.clone();
+        let max_trace_bytes = self.config.max_trace_bytes;
+        let recording = tokio::task::spawn_blocking(move || record_bytecode(&dot_id, &bytecode, max_trace_bytes))
+            .await
+            .unwrap_or_else(|e| failed_recording(format!("Recording panicked: {e}")));
+
+        let mut trace = ExecutionTrace::new(&dot.info.dot_id, dot.version as u64, recording);
+        trace.state_version = state_version;
+        (trace.inputs, trace.masked_inputs) = self.mask_inputs(&request.inputs);
+        trace.outputs = response.outputs.iter().map(|(name, value)| (name.clone(), hex::encode(value))).collect();
+        trace.events = response
+            .events
+            .iter()
+            .map(|event| TracedEvent {
+                event_type: event.event_type.clone(),
+                data: hex::encode(&event.event_data),
+            })
+            .collect();
+        trace.cap(self.config.max_trace_bytes);
+
+        let trace_id = self.store.insert(&mut trace, &dot.bytecode)?;
+        if let Some(truncation) = &trace.truncation {
+            warn!("Trace {} of dot {} was truncated: {:?}", trace_id, dot.info.dot_id, truncation);
+        }
+        info!("Recorded execution of dot {} version {} as trace {}", dot.info.dot_id, dot.version, trace_id);
+        Ok(trace_id)
+    }
+
+    /// Hex-encoded inputs without the masked ones, and the names of those that were masked
+    fn mask_inputs(&self, inputs: &HashMap<String, Vec<u8>>) -> (BTreeMap<String, String>, Vec<String>) {
+        let mut kept = BTreeMap::new();
+        let mut masked = Vec::new();
+        for (name, value) in inputs {
+            if self.config.masked_inputs.contains(name) {
+                masked.push(name.clone());
+            } else {
+                kept.insert(name.clone(), hex::encode(value));
+            }
+        }
+        masked.sort();
+        (kept, masked)
+    }
+}
+
+impl Default for ExecutionRecorder {
+    fn default() -> Self {
+        Self::new(Arc::new(TraceStore::new()), RecordingConfig::default())
+    }
+}
+
+fn record_bytecode(dot_id: &str, bytecode: &[u8], max_trace_bytes: usize) -> RecordedExecution {
+    let mut executor = trace_executor(dot_id);
+    match BytecodeFile::load_from_bytes(bytecode).map_err(Into::into).and_then(|bytecode| executor.load_bytecode(bytecode)) {
+        Ok(()) => record(&mut executor, max_trace_bytes),
+        Err(e) => failed_recording(e.to_string()),
+    }
+}
+
+fn failed_recording(error: String) -> RecordedExecution {
+    RecordedExecution {
+        host_calls: Vec::new(),
+        truncation: None,
+        final_stack: Vec::new(),
+        error: Some(error),
+        instructions_executed: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::vm_service::{DotEvent, DotInfo, ExecutionOptions};
+    use dotvm_core::bytecode::VmArchitecture;
+    use dotvm_core::opcode::stack_opcodes::StackOpcode;
+
+    fn stored_dot() -> StoredDot {
+        let mut bytecode = BytecodeFile::new(VmArchitecture::Arch64);
+        bytecode.add_instruction(StackOpcode::PushInt8.as_u8(), &[42]);
+        StoredDot {
+            info: DotInfo {
+                dot_id: "dot_checkout".to_string(),
+                ..Default::default()
+            },
+            version: 4,
+            source: String::new(),
+            bytecode: [bytecode.header.to_bytes().as_slice(), &bytecode.code].concat(),
+            abi: None,
+            architecture: VmArchitecture::Arch64,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_records_masked_trace() {
+        let config = RecordingConfig {
+            dots: HashSet::from(["dot_checkout".to_string()]),
+            masked_inputs: HashSet::from(["card_number".to_string()]),
+            ..Default::default()
+        };
+        let recorder = ExecutionRecorder::new(Arc::new(TraceStore::new()), config);
+        let request = ExecuteDotRequest {
+            dot_id: "dot_checkout".to_string(),
+            inputs: HashMap::from([("card_number".to_string(), b"4111111111111111".to_vec()), ("amount".to_string(), b"12".to_vec())]),
+            ..Default::default()
+        };
+        assert!(recorder.should_record("dot_checkout", &request));
+        let asked = ExecuteDotRequest {
+            options: Some(ExecutionOptions {
+                record_execution: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(recorder.should_record("dot_other", &asked));
+        assert!(!recorder.should_record("dot_other", &request));
+
+        let response = ExecuteDotResponse {
+            success: true,
+            outputs: HashMap::from([("result".to_string(), b"ok".to_vec())]),
+            events: vec![DotEvent {
+                event_type: "charged".to_string(),
+                event_data: vec![1, 2],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let dot = stored_dot();
+        let trace_id = recorder.record(&dot, &request, Some(9), &response).await.unwrap();
+
+        let trace = recorder.store().get(&trace_id).unwrap().unwrap();
+        assert_eq!((trace.dot_id.as_str(), trace.dot_version, trace.state_version), ("dot_checkout", 4, Some(9)));
+        assert_eq!(trace.inputs, BTreeMap::from([("amount".to_string(), hex::encode("12"))]));
+        assert_eq!(trace.masked_inputs, ["card_number"]);
+        assert!(!serde_json::to_string(&trace).unwrap().contains(&hex::encode("4111111111111111")));
+        assert_eq!(trace.outputs["result"], hex::encode("ok"));
+        assert_eq!(trace.events[0].event_type, "charged");
+        assert_eq!(recorder.store().bytecode(&trace.bytecode_sha256).unwrap(), Some(dot.bytecode));
+    }
+}
@@ -15,16 +15,45 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 //! Dot registry - manages dot storage, versioning, and metadata
-
-use std::collections::HashMap;
-use std::sync::RwLock;
+//!
+//! Deploying a name that is already registered adds a new version under the
+//! same dot ID instead of creating a new dot. Executions hold an
+//! [`ExecutionLease`] on the version that was active when they started, so
+//! activating another version only changes where new executions go. A
+//! superseded version stays in memory while it drains and for a grace period
+//! afterwards, during which it can still be re-activated as a rollback. The
+//! principal that deployed the first version owns the dot.
+//!
+//! A dot can declare the dots it calls in its deployment metadata. Each one must
+//! be deployed with an active version satisfying the declared constraint before
+//! the dot deploys, and a dot that active dots depend on is only deleted when
+//! the request forces it.
+//!
+//! Each version records the architecture its bytecode targets, and only deploys when
+//! the runtime supports an architecture that can run it.
+
+use base64::{Engine as _, engine::general_purpose};
+use dotvm_core::bytecode::{BytecodeHeader, VmArchitecture};
+use dotvm_core::dots::{DependencyGraph, DependencyType};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 use thiserror::Error;
 use tracing::{error, info};
 
 use crate::proto::vm_service::{
-    DeleteDotRequest, DeleteDotResponse, DeployDotRequest, DeployDotResponse, DeploymentMetrics, DotAbi, DotInfo, DotMetadata, DotStats, DotStatus, ListDotsRequest, ListDotsResponse,
+    ActivateDotVersionRequest, ActivateDotVersionResponse, DeleteDotRequest, DeleteDotResponse, DeployDotRequest, DeployDotResponse, DeploymentMetrics, DotAbi, DotDependency, DotInfo, DotMetadata,
+    DotStats, DotStatus, DotVersionInfo, DotVersionState, ListDotsRequest, ListDotsResponse,
 };
 
+use super::architecture::{ArchitectureConfig, ArchitectureError};
+use super::dependencies::VersionConstraint;
+
+/// How long a drained version is kept around for rollback before it is collected
+pub const DEFAULT_DRAIN_GRACE_PERIOD: Duration = Duration::from_secs(300);
+
 #[derive(Error, Debug)]
 pub enum RegistryError {
     #[error("Dot not found: {0}")]
@@ -35,45 +64,308 @@ pub enum RegistryError {
     InvalidDotSource(String),
     #[error("Compilation failed: {0}")]
     CompilationFailed(String),
+    #[error("Dot {dot_id} has no version {version}")]
+    VersionNotFound { dot_id: String, version: u32 },
+    #[error("Dot {0} has no active version")]
+    NoActiveVersion(String),
+    #[error("Dot {0} has no previous version to roll back to")]
+    NoPreviousVersion(String),
+    #[error("Invalid dependency declaration: {0}")]
+    InvalidDependency(String),
+    #[error("Dot {dot_name} has unmet dependencies: {}", missing.join(", "))]
+    MissingDependencies { dot_name: String, missing: Vec<String> },
+    #[error("Dependency cycle: {}", .0.join(" -> "))]
+    DependencyCycle(Vec<String>),
+    #[error("Dot {dot_id} is required by {}; delete those first or force the deletion", dependents.join(", "))]
+    HasDependents { dot_id: String, dependents: Vec<String> },
+    #[error(transparent)]
+    Architecture(#[from] ArchitectureError),
 }
 
 /// Dot registry manages all deployed dots
 pub struct DotRegistry {
-    dots: RwLock<HashMap<String, StoredDot>>,
+    dots: RwLock<HashMap<String, DotEntry>>,
+    drain_grace_period: Duration,
+    architectures: ArchitectureConfig,
+}
+
+/// Which versions of a dot existed and took executions at one point in time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionSnapshot {
+    pub versions: Vec<u32>,
+    pub active_version: Option<u32>,
+    pub previous_version: Option<u32>,
+}
+
+/// Steps a deployment goes through, in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeployStage {
+    /// Target architecture and ABI checks
+    Validation,
+    /// Compiling the source to bytecode for the target architecture
+    Transpilation,
+    /// Dependency checks and adding the version under the dot ID
+    Registration,
+    /// Routing new executions to the version; skipped when it is only staged
+    Activation,
+}
+
+impl DeployStage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeployStage::Validation => "validation",
+            DeployStage::Transpilation => "transpilation",
+            DeployStage::Registration => "registration",
+            DeployStage::Activation => "activation",
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct StoredDot {
     pub info: DotInfo,
+    pub version: u32,
     pub source: String,
     pub bytecode: Vec<u8>,
     pub abi: Option<DotAbi>,
+    /// Architecture the bytecode targets
+    pub architecture: VmArchitecture,
+}
+
+impl StoredDot {
+    /// Dots this version declared it depends on
+    pub fn dependencies(&self) -> &[DotDependency] {
+        self.info.metadata.as_ref().map_or(&[], |metadata| &metadata.dependencies)
+    }
+
+    /// Version a dependency constraint is checked against: the declared version, or the registry's version number
+    fn dependency_version(&self) -> String {
+        match self.info.metadata.as_ref().map(|metadata| metadata.version.trim()) {
+            Some(version) if !version.is_empty() => version.to_string(),
+            _ => self.version.to_string(),
+        }
+    }
+}
+
+/// All versions deployed under one dot ID
+struct DotEntry {
+    versions: BTreeMap<u32, DotVersion>,
+    active_version: Option<u32>,
+    /// Version that was active before the current one, the rollback target
+    previous_version: Option<u32>,
+    latest_version: u32,
+    created_at: u64,
+    /// Principal that deployed the first version
+    owner_id: String,
+}
+
+struct DotVersion {
+    dot: Arc<StoredDot>,
+    state: VersionState,
+    executions: Arc<ExecutionCounter>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VersionState {
+    Staged,
+    Active,
+    Draining { since: Instant },
+}
+
+#[derive(Default)]
+struct ExecutionCounter {
+    in_flight: AtomicUsize,
+    last_finished: Mutex<Option<Instant>>,
+}
+
+impl ExecutionCounter {
+    fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
+    }
+}
+
+/// Keeps a dot version alive for the duration of one execution
+pub struct ExecutionLease {
+    dot: Arc<StoredDot>,
+    counter: Arc<ExecutionCounter>,
+}
+
+impl Deref for ExecutionLease {
+    type Target = StoredDot;
+
+    fn deref(&self) -> &StoredDot {
+        &self.dot
+    }
+}
+
+impl Drop for ExecutionLease {
+    fn drop(&mut self) {
+        if self.counter.in_flight.fetch_sub(1, Ordering::AcqRel) == 1 {
+            *self.counter.last_finished.lock().unwrap() = Some(Instant::now());
+        }
+    }
+}
+
+impl DotEntry {
+    fn active(&self) -> Option<&DotVersion> {
+        self.active_version.and_then(|version| self.versions.get(&version))
+    }
+
+    /// Name of the newest version, which redeploys are matched against
+    fn name(&self) -> Option<&str> {
+        self.versions.values().next_back().map(|v| v.dot.info.name.as_str())
+    }
+
+    /// Route new executions to `version`; the version it replaces starts draining
+    fn activate(&mut self, version: u32) {
+        if self.active_version == Some(version) {
+            return;
+        }
+        if let Some(current) = self.active_version.and_then(|v| self.versions.get_mut(&v)) {
+            current.state = VersionState::Draining { since: Instant::now() };
+        }
+        if let Some(next) = self.versions.get_mut(&version) {
+            next.state = VersionState::Active;
+        }
+        self.previous_version = self.active_version;
+        self.active_version = Some(version);
+    }
+
+    /// Drop draining versions that have finished their executions and sat idle for `grace`
+    fn collect_drained(&mut self, grace: Duration, now: Instant) -> Vec<u32> {
+        let collectable: Vec<u32> = self
+            .versions
+            .iter()
+            .filter_map(|(&version, v)| {
+                let VersionState::Draining { since } = v.state else { return None };
+                if v.executions.in_flight() > 0 {
+                    return None;
+                }
+                let idle_since = v.executions.last_finished.lock().unwrap().map_or(since, |finished| finished.max(since));
+                (now.saturating_duration_since(idle_since) >= grace).then_some(version)
+            })
+            .collect();
+
+        for version in &collectable {
+            self.versions.remove(version);
+            if self.previous_version == Some(*version) {
+                self.previous_version = None;
+            }
+        }
+        collectable
+    }
+
+    fn to_info(&self) -> DotInfo {
+        // The entry is described by its active version, or its newest one while only staged versions exist
+        let current = self.active().or_else(|| self.versions.values().next_back()).expect("dot entry always holds at least one version");
+        let mut info = current.dot.info.clone();
+        info.created_at = self.created_at;
+        info.status = (if self.active_version.is_some() { DotStatus::Active } else { DotStatus::Inactive }) as i32;
+        info.active_version = self.active_version.unwrap_or(0);
+        info.versions = self
+            .versions
+            .iter()
+            .map(|(&version, v)| DotVersionInfo {
+                version,
+                state: match v.state {
+                    VersionState::Staged => DotVersionState::Staged,
+                    VersionState::Active => DotVersionState::Active,
+                    VersionState::Draining { .. } => DotVersionState::Draining,
+                } as i32,
+                in_flight_executions: v.executions.in_flight() as u32,
+                deployed_at: v.dot.info.updated_at,
+                architecture: v.dot.architecture.to_string(),
+            })
+            .collect();
+        info
+    }
 }
 
 impl DotRegistry {
     pub fn new() -> Self {
-        Self { dots: RwLock::new(HashMap::new()) }
+        Self::with_drain_grace_period(DEFAULT_DRAIN_GRACE_PERIOD)
+    }
+
+    pub fn with_drain_grace_period(drain_grace_period: Duration) -> Self {
+        Self {
+            dots: RwLock::new(HashMap::new()),
+            drain_grace_period,
+            architectures: ArchitectureConfig::default(),
+        }
+    }
+
+    /// Accept only dots one of the configured architectures can run
+    pub fn with_architectures(mut self, architectures: ArchitectureConfig) -> Self {
+        self.architectures = architectures;
+        self
+    }
+
+    pub fn drain_grace_period(&self) -> Duration {
+        self.drain_grace_period
+    }
+
+    pub fn architectures(&self) -> &ArchitectureConfig {
+        &self.architectures
     }
 
     pub async fn deploy_dot(&self, request: DeployDotRequest) -> Result<DeployDotResponse, RegistryError> {
+        self.deploy_dot_observed(request, &mut |_| {}).await
+    }
+
+    /// Deploy a dot, calling `enter` as each [`DeployStage`] starts. A stage has finished once
+    /// the next one is entered or the deployment returns.
+    pub async fn deploy_dot_observed(&self, request: DeployDotRequest, enter: &mut (dyn FnMut(DeployStage) + Send)) -> Result<DeployDotResponse, RegistryError> {
         info!("Deploying dot: {}", request.dot_name);
 
-        // Generate dot ID
-        let dot_id = self.generate_dot_id(&request.dot_name);
+        let stage_only = request.options.as_ref().is_some_and(|options| options.stage_only);
 
-        // Check if dot already exists
-        {
-            let dots = self.dots.read().unwrap();
-            if dots.contains_key(&dot_id) {
-                return Err(RegistryError::DotAlreadyExists(dot_id));
-            }
-        }
+        enter(DeployStage::Validation);
+        let target = self.architectures.target(request.options.as_ref().map_or("", |options| options.target_architecture.as_str()))?;
+        // TODO: Generate ABI from dot source
+        let mut abi = self.generate_abi_from_source(&request.dot_source)?;
+        abi.dependencies = request.metadata.as_ref().map(|metadata| metadata.dependencies.clone()).unwrap_or_default();
+        abi.deterministic = request.metadata.as_ref().is_some_and(|metadata| metadata.deterministic);
 
+        enter(DeployStage::Transpilation);
         // TODO: Compile dot source to bytecode
         let bytecode = self.compile_dot_source(&request.dot_source)?;
+        // Precompiled bytecode names its architecture in its header; compiled source targets the requested one
+        let architecture = ArchitectureConfig::detect(&bytecode).unwrap_or(target);
+        self.architectures.select(architecture)?;
 
-        // TODO: Generate ABI from dot source
-        let abi = self.generate_abi_from_source(&request.dot_source)?;
+        enter(DeployStage::Registration);
+        let now = chrono::Utc::now().timestamp() as u64;
+        let mut dots = self.dots.write().unwrap();
+        self.collect_locked(&mut dots);
+
+        Self::check_dependencies(&dots, &request.dot_name, &abi.dependencies)?;
+
+        // Redeploying a known name adds a version under the existing dot ID
+        let existing = Self::find_by_name(&dots, &request.dot_name).map(|(id, _)| id.clone());
+        let dot_id = match existing {
+            Some(dot_id) => dot_id,
+            None => {
+                let dot_id = self.generate_dot_id(&request.dot_name);
+                if dots.contains_key(&dot_id) {
+                    return Err(RegistryError::DotAlreadyExists(dot_id));
+                }
+                dots.insert(
+                    dot_id.clone(),
+                    DotEntry {
+                        versions: BTreeMap::new(),
+                        active_version: None,
+                        previous_version: None,
+                        latest_version: 0,
+                        created_at: now,
+                        owner_id: request.deployer_id.clone(),
+                    },
+                );
+                dot_id
+            }
+        };
+
+        let entry = dots.get_mut(&dot_id).expect("entry inserted above");
+        let version = entry.latest_version + 1;
 
         // Create dot info
         let dot_info = DotInfo {
@@ -81,8 +373,8 @@ impl DotRegistry {
             name: request.dot_name.clone(),
             metadata: request.metadata.clone(),
             status: DotStatus::Active as i32,
-            created_at: chrono::Utc::now().timestamp() as u64,
-            updated_at: chrono::Utc::now().timestamp() as u64,
+            created_at: entry.created_at,
+            updated_at: now,
             abi: Some(abi.clone()),
             stats: Some(DotStats {
                 execution_count: 0,
@@ -91,22 +383,41 @@ impl DotRegistry {
                 error_count: 0,
                 last_executed_at: 0,
             }),
+            active_version: 0,
+            versions: vec![],
+            owner_id: entry.owner_id.clone(),
+            architecture: architecture.to_string(),
         };
 
         // Store dot
         let stored_dot = StoredDot {
-            info: dot_info.clone(),
+            info: dot_info,
+            version,
             source: request.dot_source,
             bytecode: bytecode.clone(),
             abi: Some(abi.clone()),
+            architecture,
         };
 
-        {
-            let mut dots = self.dots.write().unwrap();
-            dots.insert(dot_id.clone(), stored_dot);
+        entry.latest_version = version;
+        entry.versions.insert(
+            version,
+            DotVersion {
+                dot: Arc::new(stored_dot),
+                state: VersionState::Staged,
+                executions: Arc::default(),
+            },
+        );
+        if !stage_only {
+            enter(DeployStage::Activation);
+            entry.activate(version);
         }
 
-        info!("Successfully deployed dot: {}", dot_id);
+        if stage_only {
+            info!("Staged version {} of dot: {} ({})", version, dot_id, architecture);
+        } else {
+            info!("Successfully deployed version {} of dot: {} ({})", version, dot_id, architecture);
+        }
 
         Ok(DeployDotResponse {
             success: true,
@@ -120,18 +431,120 @@ impl DotRegistry {
                 optimization_passes: 2,
                 ui_generated: false,
             }),
+            version,
+            activated: !stage_only,
+            // Set by the service once a redeploy passes verification
+            checkpoint_id: String::new(),
+            architecture: architecture.to_string(),
         })
     }
 
+    /// Currently active version of a dot
     pub async fn get_dot(&self, dot_id: &str) -> Result<StoredDot, RegistryError> {
         let dots = self.dots.read().unwrap();
-        dots.get(dot_id).cloned().ok_or_else(|| RegistryError::DotNotFound(dot_id.to_string()))
+        let entry = dots.get(dot_id).ok_or_else(|| RegistryError::DotNotFound(dot_id.to_string()))?;
+        entry.active().map(|v| v.dot.as_ref().clone()).ok_or_else(|| RegistryError::NoActiveVersion(dot_id.to_string()))
     }
 
-    pub async fn list_dots(&self, _request: ListDotsRequest) -> Result<ListDotsResponse, RegistryError> {
+    /// Pin the active version of a dot for one execution
+    ///
+    /// The version stays loaded until the returned lease is dropped, even if
+    /// another version is activated in the meantime.
+    pub fn begin_execution(&self, dot_id: &str) -> Result<ExecutionLease, RegistryError> {
+        // Leases are only handed out under the lock, so collection never races a new execution
         let dots = self.dots.read().unwrap();
+        let entry = dots.get(dot_id).ok_or_else(|| RegistryError::DotNotFound(dot_id.to_string()))?;
+        let active = entry.active().ok_or_else(|| RegistryError::NoActiveVersion(dot_id.to_string()))?;
+        active.executions.in_flight.fetch_add(1, Ordering::AcqRel);
+        Ok(ExecutionLease {
+            dot: active.dot.clone(),
+            counter: active.executions.clone(),
+        })
+    }
+
+    /// Route new executions to `version`, or back to the previously active version on rollback
+    pub async fn activate_version(&self, request: ActivateDotVersionRequest) -> Result<ActivateDotVersionResponse, RegistryError> {
+        let mut dots = self.dots.write().unwrap();
+        self.collect_locked(&mut dots);
 
-        let dot_infos: Vec<DotInfo> = dots.values().map(|stored_dot| stored_dot.info.clone()).collect();
+        let entry = dots.get_mut(&request.dot_id).ok_or_else(|| RegistryError::DotNotFound(request.dot_id.clone()))?;
+        let version = if request.rollback {
+            entry.previous_version.ok_or_else(|| RegistryError::NoPreviousVersion(request.dot_id.clone()))?
+        } else {
+            request.version
+        };
+        if !entry.versions.contains_key(&version) {
+            return Err(RegistryError::VersionNotFound { dot_id: request.dot_id, version });
+        }
+
+        let previous = entry.active_version;
+        entry.activate(version);
+        info!("Activated version {} of dot: {} (was {:?})", version, request.dot_id, previous);
+
+        Ok(ActivateDotVersionResponse {
+            success: true,
+            active_version: version,
+            previous_version: previous.unwrap_or(0),
+            error_message: String::new(),
+        })
+    }
+
+    /// ID of the dot registered under `name`, which a deploy of that name adds a version to
+    pub fn dot_id_for_name(&self, name: &str) -> Option<String> {
+        let dots = self.dots.read().unwrap();
+        Self::find_by_name(&dots, name).map(|(id, _)| id.clone())
+    }
+
+    pub fn version_snapshot(&self, dot_id: &str) -> Result<VersionSnapshot, RegistryError> {
+        let dots = self.dots.read().unwrap();
+        let entry = dots.get(dot_id).ok_or_else(|| RegistryError::DotNotFound(dot_id.to_string()))?;
+        Ok(VersionSnapshot {
+            versions: entry.versions.keys().copied().collect(),
+            active_version: entry.active_version,
+            previous_version: entry.previous_version,
+        })
+    }
+
+    /// Route a dot as it was when `snapshot` was taken, dropping versions deployed since
+    ///
+    /// Executions already running on a dropped version finish on it. Version
+    /// numbers are not reissued, so the next deploy still gets a new one.
+    /// Returns the dropped versions.
+    pub fn restore_versions(&self, dot_id: &str, snapshot: &VersionSnapshot) -> Result<Vec<u32>, RegistryError> {
+        let mut dots = self.dots.write().unwrap();
+        let entry = dots.get_mut(dot_id).ok_or_else(|| RegistryError::DotNotFound(dot_id.to_string()))?;
+        if let Some(version) = snapshot.active_version.filter(|version| !entry.versions.contains_key(version)) {
+            return Err(RegistryError::VersionNotFound { dot_id: dot_id.to_string(), version });
+        }
+
+        let dropped: Vec<u32> = entry.versions.keys().copied().filter(|version| !snapshot.versions.contains(version)).collect();
+        if dropped.len() == entry.versions.len() {
+            return Err(RegistryError::NoActiveVersion(dot_id.to_string()));
+        }
+        for version in &dropped {
+            entry.versions.remove(version);
+        }
+
+        let now = Instant::now();
+        for (&version, v) in entry.versions.iter_mut() {
+            if Some(version) == snapshot.active_version {
+                v.state = VersionState::Active;
+            } else if v.state == VersionState::Active {
+                v.state = VersionState::Draining { since: now };
+            }
+        }
+        entry.active_version = snapshot.active_version;
+        entry.previous_version = snapshot.previous_version.filter(|version| entry.versions.contains_key(version));
+
+        info!("Restored dot {} to active version {:?}, dropping versions {:?}", dot_id, snapshot.active_version, dropped);
+        Ok(dropped)
+    }
+
+    pub async fn list_dots(&self, _request: ListDotsRequest) -> Result<ListDotsResponse, RegistryError> {
+        let mut dots = self.dots.write().unwrap();
+        self.collect_locked(&mut dots);
+
+        let dot_infos: Vec<DotInfo> = dots.values().map(DotEntry::to_info).collect();
 
         Ok(ListDotsResponse {
             dots: dot_infos.clone(),
@@ -141,26 +554,144 @@ impl DotRegistry {
         })
     }
 
+    /// Remove a dot and all its versions
+    ///
+    /// Dots whose active version depends on it block the deletion unless the request forces it.
     pub async fn delete_dot(&self, request: DeleteDotRequest) -> Result<DeleteDotResponse, RegistryError> {
         let mut dots = self.dots.write().unwrap();
-
-        if dots.remove(&request.dot_id).is_some() {
-            info!("Successfully deleted dot: {}", request.dot_id);
-            Ok(DeleteDotResponse {
-                success: true,
-                error_message: String::new(),
+        let entry = dots.get(&request.dot_id).ok_or_else(|| RegistryError::DotNotFound(request.dot_id.clone()))?;
+
+        let name = entry.name().unwrap_or_default();
+        let mut dependents: Vec<String> = dots
+            .iter()
+            .filter(|(id, _)| **id != request.dot_id)
+            .filter_map(|(id, other)| {
+                let active = other.active()?;
+                active
+                    .dot
+                    .dependencies()
+                    .iter()
+                    .any(|dependency| dependency.dot_name == name)
+                    .then(|| format!("{} ({})", active.dot.info.name, id))
             })
-        } else {
-            Err(RegistryError::DotNotFound(request.dot_id))
+            .collect();
+        dependents.sort();
+
+        if !dependents.is_empty() {
+            if !request.force {
+                return Err(RegistryError::HasDependents { dot_id: request.dot_id, dependents });
+            }
+            info!("Force-deleting dot {} still required by: {}", request.dot_id, dependents.join(", "));
         }
+
+        dots.remove(&request.dot_id);
+        info!("Successfully deleted dot: {}", request.dot_id);
+        Ok(DeleteDotResponse {
+            success: true,
+            error_message: String::new(),
+        })
+    }
+
+    /// Drop drained versions whose grace period has elapsed, returning how many were collected
+    ///
+    /// Deploy, activate and list also collect on the way in.
+    pub fn collect_drained_versions(&self) -> usize {
+        let mut dots = self.dots.write().unwrap();
+        self.collect_locked(&mut dots)
+    }
+
+    fn collect_locked(&self, dots: &mut HashMap<String, DotEntry>) -> usize {
+        let now = Instant::now();
+        let mut collected = 0;
+        for (dot_id, entry) in dots.iter_mut() {
+            for version in entry.collect_drained(self.drain_grace_period, now) {
+                info!("Collected drained version {} of dot: {}", version, dot_id);
+                collected += 1;
+            }
+        }
+        collected
     }
 
     // Private helper methods
+    fn find_by_name<'a>(dots: &'a HashMap<String, DotEntry>, name: &str) -> Option<(&'a String, &'a DotEntry)> {
+        dots.iter().find(|(_, entry)| entry.name() == Some(name))
+    }
+
+    /// Check that every dependency of `dot_name` is active in a version it accepts,
+    /// and that deploying it would not close a dependency cycle among active dots
+    fn check_dependencies(dots: &HashMap<String, DotEntry>, dot_name: &str, dependencies: &[DotDependency]) -> Result<(), RegistryError> {
+        let mut missing = Vec::new();
+        for dependency in dependencies {
+            let constraint = VersionConstraint::parse(&dependency.version_constraint).map_err(|reason| RegistryError::InvalidDependency(format!("{}: {}", dependency.dot_name, reason)))?;
+            let required = if constraint.is_any() {
+                dependency.dot_name.clone()
+            } else {
+                format!("{} {}", dependency.dot_name, constraint)
+            };
+            if dependency.dot_name == dot_name {
+                return Err(RegistryError::DependencyCycle(vec![dot_name.to_string(), dot_name.to_string()]));
+            }
+
+            match Self::find_by_name(dots, &dependency.dot_name).map(|(_, entry)| entry.active()) {
+                None => missing.push(format!("{} (not deployed)", required)),
+                Some(None) => missing.push(format!("{} (no active version)", required)),
+                Some(Some(active)) => {
+                    let version = active.dot.dependency_version();
+                    if !constraint.matches(&version) {
+                        missing.push(format!("{} (active version is {})", required, version));
+                    }
+                }
+            }
+        }
+        if !missing.is_empty() {
+            return Err(RegistryError::MissingDependencies {
+                dot_name: dot_name.to_string(),
+                missing,
+            });
+        }
+
+        // A redeploy can declare a dependency on one of its own dependents
+        let mut graph = DependencyGraph::new();
+        for entry in dots.values() {
+            let Some(active) = entry.active() else { continue };
+            if active.dot.info.name == dot_name {
+                continue;
+            }
+            for dependency in active.dot.dependencies() {
+                graph.add_dependency(&active.dot.info.name, &dependency.dot_name, DependencyType::Prerequisite);
+            }
+        }
+        for dependency in dependencies {
+            graph.add_dependency(dot_name, &dependency.dot_name, DependencyType::Prerequisite);
+        }
+        match graph.find_cycle() {
+            Some(mut cycle) => {
+                // Report the loop starting from the dot being deployed
+                cycle.pop();
+                if let Some(start) = cycle.iter().position(|name| name == dot_name) {
+                    cycle.rotate_left(start);
+                }
+                cycle.push(cycle[0].clone());
+                Err(RegistryError::DependencyCycle(cycle))
+            }
+            None => Ok(()),
+        }
+    }
+
     fn generate_dot_id(&self, name: &str) -> String {
         format!("dot_{}_{}", name.to_lowercase().replace(" ", "_"), uuid::Uuid::new_v4().to_string().replace("-", "")[..8].to_string())
     }
 
     fn compile_dot_source(&self, source: &str) -> Result<Vec<u8>, RegistryError> {
+        // Streamed deployments and the gateway carry already compiled bytecode, base64-encoded
+        if let Ok(bytecode) = general_purpose::STANDARD.decode(source.trim())
+            && bytecode.starts_with(&BytecodeHeader::MAGIC_NUMBER)
+        {
+            BytecodeHeader::from_bytes(&bytecode).map_err(|e| RegistryError::InvalidDotSource(e.to_string()))?;
+            info!("Deploying precompiled bytecode ({} bytes)", bytecode.len());
+            return Ok(bytecode);
+        }
+
         // TODO: Implement actual dot compilation
         // For now, return mock bytecode
         info!("Compiling dot source ({} chars)", source.len());
@@ -189,6 +720,225 @@ impl DotRegistry {
             paradots: vec![],  // TODO: Parse from source
             ui_hints: None,    // TODO: Generate UI hints
             permissions: None, // TODO: Parse permissions
+            operations: vec![],
+            state_schema: vec![],
+            dependencies: vec![],
+            deterministic: false,
+            events: vec![],
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::vm_service::DeploymentOptions;
+
+    fn deploy_request(name: &str, source: &str, stage_only: bool) -> DeployDotRequest {
+        DeployDotRequest {
+            dot_name: name.to_string(),
+            dot_source: source.to_string(),
+            metadata: None,
+            deployer_id: "tester".to_string(),
+            options: Some(DeploymentOptions { stage_only, ..Default::default() }),
+        }
+    }
+
+    fn depending_on(name: &str, dependencies: &[(&str, &str)]) -> DeployDotRequest {
+        DeployDotRequest {
+            metadata: Some(DotMetadata {
+                dependencies: dependencies
+                    .iter()
+                    .map(|(dot_name, version_constraint)| DotDependency {
+                        dot_name: dot_name.to_string(),
+                        version_constraint: version_constraint.to_string(),
+                    })
+                    .collect(),
+                ..Default::default()
+            }),
+            ..deploy_request(name, "dot", false)
+        }
+    }
+
+    async fn versions(registry: &DotRegistry) -> Vec<(u32, DotVersionState, u32)> {
+        let listed = registry.list_dots(ListDotsRequest::default()).await.unwrap();
+        listed.dots[0].versions.iter().map(|v| (v.version, v.state(), v.in_flight_executions)).collect()
+    }
+
+    #[tokio::test]
+    async fn test_redeploy_drains_previous_version() {
+        let registry = DotRegistry::new();
+        let first = registry.deploy_dot(deploy_request("counter", "v1", false)).await.unwrap();
+        let lease = registry.begin_execution(&first.dot_id).unwrap();
+
+        let second = registry.deploy_dot(deploy_request("counter", "v2", false)).await.unwrap();
+        assert_eq!(second.dot_id, first.dot_id);
+        assert_eq!(second.version, 2);
+
+        // The in-flight execution keeps running on version 1, new ones go to version 2
+        assert_eq!(lease.version, 1);
+        assert_eq!(lease.source, "v1");
+        assert_eq!(registry.begin_execution(&first.dot_id).unwrap().source, "v2");
+        assert_eq!(versions(&registry).await, vec![(1, DotVersionState::Draining, 1), (2, DotVersionState::Active, 0)]);
+
+        let listed = registry.list_dots(ListDotsRequest::default()).await.unwrap();
+        assert_eq!(listed.total_count, 1);
+        assert_eq!(listed.dots[0].active_version, 2);
+    }
+
+    #[tokio::test]
+    async fn test_drained_version_collected_after_grace_period() {
+        let registry = DotRegistry::with_drain_grace_period(Duration::ZERO);
+        let first = registry.deploy_dot(deploy_request("counter", "v1", false)).await.unwrap();
+        let lease = registry.begin_execution(&first.dot_id).unwrap();
+        registry.deploy_dot(deploy_request("counter", "v2", false)).await.unwrap();
+
+        assert_eq!(registry.collect_drained_versions(), 0);
+        drop(lease);
+        assert_eq!(registry.collect_drained_versions(), 1);
+        assert_eq!(versions(&registry).await, vec![(2, DotVersionState::Active, 0)]);
+
+        // The rollback target went with it
+        let rollback = ActivateDotVersionRequest {
+            dot_id: first.dot_id.clone(),
+            version: 0,
+            rollback: true,
+        };
+        assert!(matches!(registry.activate_version(rollback).await, Err(RegistryError::NoPreviousVersion(_))));
+
+        let kept = DotRegistry::with_drain_grace_period(Duration::from_secs(3600));
+        let first = kept.deploy_dot(deploy_request("counter", "v1", false)).await.unwrap();
+        kept.deploy_dot(deploy_request("counter", "v2", false)).await.unwrap();
+        assert_eq!(kept.collect_drained_versions(), 0);
+        assert!(kept.get_dot(&first.dot_id).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_staged_version_activation_and_rollback() {
+        let registry = DotRegistry::new();
+        let first = registry.deploy_dot(deploy_request("counter", "v1", false)).await.unwrap();
+        let staged = registry.deploy_dot(deploy_request("counter", "v2", true)).await.unwrap();
+        assert!(!staged.activated);
+        assert_eq!(registry.get_dot(&first.dot_id).await.unwrap().version, 1);
+        assert_eq!(versions(&registry).await, vec![(1, DotVersionState::Active, 0), (2, DotVersionState::Staged, 0)]);
+
+        let activated = registry
+            .activate_version(ActivateDotVersionRequest {
+                dot_id: first.dot_id.clone(),
+                version: 2,
+                rollback: false,
+            })
+            .await
+            .unwrap();
+        assert_eq!((activated.active_version, activated.previous_version), (2, 1));
+        assert_eq!(registry.get_dot(&first.dot_id).await.unwrap().source, "v2");
+
+        let rolled_back = registry
+            .activate_version(ActivateDotVersionRequest {
+                dot_id: first.dot_id.clone(),
+                version: 0,
+                rollback: true,
+            })
+            .await
+            .unwrap();
+        assert_eq!((rolled_back.active_version, rolled_back.previous_version), (1, 2));
+        assert_eq!(registry.get_dot(&first.dot_id).await.unwrap().source, "v1");
+
+        let missing = ActivateDotVersionRequest {
+            dot_id: first.dot_id,
+            version: 7,
+            rollback: false,
+        };
+        assert!(matches!(registry.activate_version(missing).await, Err(RegistryError::VersionNotFound { version: 7, .. })));
+    }
+
+    #[tokio::test]
+    async fn test_staged_only_dot_has_no_active_version() {
+        let registry = DotRegistry::new();
+        let staged = registry.deploy_dot(deploy_request("counter", "v1", true)).await.unwrap();
+
+        assert!(matches!(registry.begin_execution(&staged.dot_id), Err(RegistryError::NoActiveVersion(_))));
+        let listed = registry.list_dots(ListDotsRequest::default()).await.unwrap();
+        assert_eq!(listed.dots[0].status(), DotStatus::Inactive);
+        assert_eq!(listed.dots[0].active_version, 0);
+    }
+
+    #[tokio::test]
+    async fn test_deploy_requires_active_dependencies() {
+        let registry = DotRegistry::new();
+        let missing = registry.deploy_dot(depending_on("pricing", &[("tax", ">=2"), ("rates", "")])).await.unwrap_err();
+        let RegistryError::MissingDependencies { dot_name, missing } = missing else {
+            panic!("expected missing dependencies, got {missing:?}");
+        };
+        assert_eq!(dot_name, "pricing");
+        assert_eq!(missing, vec!["tax >=2 (not deployed)", "rates (not deployed)"]);
+
+        registry.deploy_dot(deploy_request("rates", "v1", false)).await.unwrap();
+        registry.deploy_dot(deploy_request("tax", "v1", true)).await.unwrap();
+        let inactive = registry.deploy_dot(depending_on("pricing", &[("tax", ">=2"), ("rates", "")])).await.unwrap_err();
+        assert!(matches!(&inactive, RegistryError::MissingDependencies { missing, .. } if missing == &["tax >=2 (no active version)"]));
+
+        registry.deploy_dot(deploy_request("tax", "v2", false)).await.unwrap();
+        let deployed = registry.deploy_dot(depending_on("pricing", &[("tax", ">=2"), ("rates", "")])).await.unwrap();
+        assert_eq!(deployed.abi.unwrap().dependencies.len(), 2);
+
+        assert!(matches!(
+            registry.deploy_dot(depending_on("pricing", &[("tax", ">=two")])).await,
+            Err(RegistryError::InvalidDependency(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_dependency_cycles_and_forced_delete() {
+        let registry = DotRegistry::new();
+        let tax = registry.deploy_dot(deploy_request("tax", "v1", false)).await.unwrap();
+        registry.deploy_dot(depending_on("pricing", &[("tax", "")])).await.unwrap();
+
+        // Redeploying tax on top of pricing would close the loop
+        let cycle = registry.deploy_dot(depending_on("tax", &[("pricing", "")])).await.unwrap_err();
+        assert!(matches!(&cycle, RegistryError::DependencyCycle(path) if path == &["tax", "pricing", "tax"]), "{cycle:?}");
+        assert!(matches!(registry.deploy_dot(depending_on("tax", &[("tax", "")])).await, Err(RegistryError::DependencyCycle(_))));
+
+        let delete = |force| DeleteDotRequest {
+            dot_id: tax.dot_id.clone(),
+            requester_id: "tester".to_string(),
+            force,
+        };
+        let refused = registry.delete_dot(delete(false)).await.unwrap_err();
+        assert!(matches!(&refused, RegistryError::HasDependents { dependents, .. } if dependents.len() == 1 && dependents[0].starts_with("pricing (")));
+        assert!(registry.get_dot(&tax.dot_id).await.is_ok());
+
+        assert!(registry.delete_dot(delete(true)).await.unwrap().success);
+        assert!(matches!(registry.get_dot(&tax.dot_id).await, Err(RegistryError::DotNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_architecture_is_detected_and_checked_at_deploy() {
+        let registry = DotRegistry::new();
+        let precompiled = |architecture: VmArchitecture| {
+            let bytecode = [BytecodeHeader::new(architecture).to_bytes().as_slice(), &[0x01, 0x02]].concat();
+            deploy_request("sensor", &general_purpose::STANDARD.encode(bytecode), false)
+        };
+
+        // The header decides, whatever the deployment asked the compiler for
+        let mut request = precompiled(VmArchitecture::Arch128);
+        request.options.as_mut().unwrap().target_architecture = "arch64".to_string();
+        let deployed = registry.deploy_dot(request).await.unwrap();
+        assert_eq!(deployed.architecture, "arch128");
+        assert_eq!(registry.get_dot(&deployed.dot_id).await.unwrap().architecture, VmArchitecture::Arch128);
+
+        // Source is compiled for the runtime default unless the deployment names a target
+        registry.deploy_dot(deploy_request("sensor", "v2", true)).await.unwrap();
+        let listed = registry.list_dots(ListDotsRequest::default()).await.unwrap();
+        assert_eq!(listed.dots[0].architecture, "arch128");
+        let architectures: Vec<&str> = listed.dots[0].versions.iter().map(|v| v.architecture.as_str()).collect();
+        assert_eq!(architectures, ["arch128", "arch64"]);
+
+        let unsupported = registry.deploy_dot(precompiled(VmArchitecture::Arch512)).await.unwrap_err();
+        assert_eq!(unsupported.to_string(), "Dot targets arch512, but this runtime executes at most arch128");
+        let mut request = deploy_request("sensor", "v3", false);
+        request.options.as_mut().unwrap().target_architecture = "WASM".to_string();
+        assert!(matches!(registry.deploy_dot(request).await, Err(RegistryError::Architecture(ArchitectureError::InvalidTarget(_)))));
+        assert_eq!(registry.version_snapshot(&deployed.dot_id).unwrap().versions, [1, 2]);
+    }
+}
@@ -16,52 +16,325 @@
 
 //! Dots service implementation
 
+use dotvm_common::telemetry::{REQUEST_ID_HEADER, TRACEPARENT_HEADER, TraceContext};
+use dotvm_core::vm::execution_controller::{DotQuotaStatus, ExecutionError, QuotaConfig, ResourceRequirements, Task, TaskPriority, resource_allocation::ResourceAllocator};
+use dotvm_runtime::rollback::lib::{RollbackError, SystemState};
+use dotvm_runtime::rollback::verification::DefaultConsistencyVerifier;
+use dotvm_runtime::rollback::{CheckpointCategory, ConsistencyVerifier, OperationCheckpoint, OperationCheckpoints, VerificationResult};
 use std::sync::Arc;
-use tonic::{Request, Response, Result as TonicResult, Status};
-use tracing::{error, info, instrument};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tonic::{Request, Response, Result as TonicResult, Status, Streaming};
+use tracing::{Instrument, error, info, info_span, instrument};
 
 use crate::proto::vm_service::{
+    ActivateDotVersionRequest,
+    ActivateDotVersionResponse,
     DeleteDotRequest,
     DeleteDotResponse,
+    DeployDotChunk,
+    DeployDotProgress,
     DeployDotRequest,
     DeployDotResponse,
+    DeployStageState,
     DeploymentMetrics,
+    DebugRequest,
+    DebugResponse,
     // Types
     DotInfo,
     DotMetadata,
+    DotCacheStats,
+    DotQuota,
+    DotQuotaUsage,
     DotStats,
     DotStatus,
     ExecuteDotRequest,
     ExecuteDotResponse,
+    ExecuteParaDotRequest,
+    ExecuteParaDotResponse,
     ExecutionMetrics,
+    GetDotCacheStatsRequest,
+    GetDotCacheStatsResponse,
+    GetDotQuotaRequest,
+    GetDotQuotaResponse,
     GetDotStateRequest,
     GetDotStateResponse,
+    GetStateDiffRequest,
+    GetStateDiffResponse,
+    InteractiveExecutionRequest,
+    InteractiveExecutionResponse,
+    ListCheckpointsRequest,
+    ListCheckpointsResponse,
     ListDotsRequest,
     ListDotsResponse,
+    ListParaDotsRequest,
+    ListParaDotsResponse,
     LogEntry,
+    OperationCheckpoint as OperationCheckpointInfo,
+    ParaDotInfo,
+    RestoreCheckpointRequest,
+    RestoreCheckpointResponse,
+    SetDotCachingRequest,
+    SetDotCachingResponse,
+    SetDotQuotaRequest,
+    SetDotQuotaResponse,
+    SpawnParaDotRequest,
+    SpawnParaDotResponse,
+    TerminateParaDotRequest,
+    TerminateParaDotResponse,
+    spawn_para_dot_request,
 };
 
-use super::executor::DotExecutor;
-use super::registry::DotRegistry;
+use super::architecture::{ArchitectureConfig, ArchitectureError};
+use super::checkpoints::{self, DotCheckpointTarget};
+use super::debugger::DotDebugger;
+use super::executor::{DotExecutor, ExecutorError};
+use super::interactive;
+use super::memo::{ResultCache, ResultCacheStats};
+use super::paradots::{ParaDotError, ParaDotQuery, ParaDotSource, ParaDotSpec, ParaDotSupervisor};
+use super::permissions::{Caller, DEFAULT_OPERATION, PermissionError, PermissionEvaluator};
+use super::recording::ExecutionRecorder;
+use super::registry::{DeployStage, DotRegistry, RegistryError};
+use super::state::{DotStateStore, StateStoreError};
+use super::upload::{self, DEFAULT_MAX_ARTIFACT_BYTES};
+use crate::services::abi::registry::AbiRegistry;
+use crate::services::metrics::latency::{Latency, LatencyHistograms};
+
+/// Allocator task ids of dot executions start here, clear of the execution controller's and ParaDots' ids
+const DOT_EXECUTION_TASK_IDS: u64 = 1 << 61;
+
+/// Gives back what an execution holds from its dot's quota, also when the request is dropped midway
+struct AllocationGuard<'a> {
+    allocator: &'a ResourceAllocator,
+    task_id: u64,
+}
+
+impl Drop for AllocationGuard<'_> {
+    fn drop(&mut self) {
+        self.allocator.release(self.task_id);
+    }
+}
 
 /// Dots service handles all dot-related operations
 pub struct DotsService {
     registry: Arc<DotRegistry>,
     executor: Arc<DotExecutor>,
+    debugger: Arc<DotDebugger>,
+    resource_allocator: Arc<ResourceAllocator>,
+    /// ParaDots spawned under deployed dots, charged to their parents' quotas
+    paradots: Arc<ParaDotSupervisor>,
+    checkpoints: Arc<OperationCheckpoints>,
+    /// Checks a redeploy must pass, or it is rolled back to the checkpoint taken before it
+    redeploy_verifier: DefaultConsistencyVerifier,
+    /// Largest bytecode accepted from a streamed deployment
+    max_artifact_bytes: u64,
+    /// Who may execute which operation, from the permissions of the registered ABIs
+    permissions: Arc<PermissionEvaluator>,
+    next_task_id: AtomicU64,
 }
 
 impl DotsService {
     pub fn new() -> Self {
-        Self {
+        Self::with_resource_allocator(Arc::new(ResourceAllocator::new()))
+    }
+
+    /// Uses an allocator shared with the execution controller so quotas set here are enforced there
+    pub fn with_resource_allocator(resource_allocator: Arc<ResourceAllocator>) -> Self {
+        let service = Self {
             registry: Arc::new(DotRegistry::new()),
             executor: Arc::new(DotExecutor::new()),
-        }
+            debugger: Arc::new(DotDebugger::new()),
+            paradots: Arc::new(ParaDotSupervisor::new(resource_allocator.clone())),
+            resource_allocator,
+            checkpoints: Arc::new(OperationCheckpoints::new()),
+            redeploy_verifier: checkpoints::redeploy_verifier(),
+            max_artifact_bytes: DEFAULT_MAX_ARTIFACT_BYTES,
+            permissions: PermissionEvaluator::new(Arc::new(AbiRegistry::new())),
+            next_task_id: AtomicU64::new(DOT_EXECUTION_TASK_IDS),
+        };
+        service.register_checkpoint_target();
+        service
     }
 
-    #[instrument(skip(self, request))]
+    /// Bounds the memory held by memoized results of deterministic dots
+    pub fn with_result_cache_capacity(mut self, capacity_bytes: usize) -> Self {
+        let recorder = self.executor.recorder().clone();
+        let latencies = self.executor.latencies().clone();
+        let architectures = self.executor.architectures().clone();
+        let state_store = self.executor.state_store().clone();
+        self.executor = Arc::new(
+            DotExecutor::with_result_cache(Arc::new(ResultCache::new(capacity_bytes)))
+                .with_state_store(state_store)
+                .with_recorder(recorder)
+                .with_latencies(latencies)
+                .with_architectures(architectures),
+        );
+        self.register_checkpoint_target();
+        self
+    }
+
+    /// Records the configured executions, and those that ask for it, through `recorder`
+    pub fn with_recording(mut self, recorder: Arc<ExecutionRecorder>) -> Self {
+        let result_cache = self.executor.result_cache().clone();
+        let latencies = self.executor.latencies().clone();
+        let architectures = self.executor.architectures().clone();
+        let state_store = self.executor.state_store().clone();
+        self.executor = Arc::new(
+            DotExecutor::with_result_cache(result_cache)
+                .with_state_store(state_store)
+                .with_recorder(recorder)
+                .with_latencies(latencies)
+                .with_architectures(architectures),
+        );
+        self.register_checkpoint_target();
+        self
+    }
+
+    /// Records per-dot queue wait, execution and state access times into `latencies`,
+    /// e.g. the histograms the metrics service reports
+    pub fn with_latencies(mut self, latencies: Arc<LatencyHistograms>) -> Self {
+        let result_cache = self.executor.result_cache().clone();
+        let recorder = self.executor.recorder().clone();
+        let architectures = self.executor.architectures().clone();
+        let state_store = self.executor.state_store().clone();
+        self.executor = Arc::new(
+            DotExecutor::with_result_cache(result_cache)
+                .with_state_store(state_store)
+                .with_recorder(recorder)
+                .with_latencies(latencies)
+                .with_architectures(architectures),
+        );
+        self.register_checkpoint_target();
+        self
+    }
+
+    /// Keeps dot state in `state_store`, e.g. one persisted next to the document store
+    pub fn with_state_store(mut self, state_store: Arc<DotStateStore>) -> Self {
+        let result_cache = self.executor.result_cache().clone();
+        let recorder = self.executor.recorder().clone();
+        let latencies = self.executor.latencies().clone();
+        let architectures = self.executor.architectures().clone();
+        self.executor = Arc::new(
+            DotExecutor::with_result_cache(result_cache)
+                .with_state_store(state_store)
+                .with_recorder(recorder)
+                .with_latencies(latencies)
+                .with_architectures(architectures),
+        );
+        self.register_checkpoint_target();
+        self
+    }
+
+    /// Keeps superseded dot versions available for rollback this long after they drain
+    pub fn with_drain_grace_period(mut self, grace_period: Duration) -> Self {
+        let architectures = self.registry.architectures().clone();
+        self.registry = Arc::new(DotRegistry::with_drain_grace_period(grace_period).with_architectures(architectures));
+        self.register_checkpoint_target();
+        self
+    }
+
+    /// Deploys and executes dots on the configured architectures only
+    pub fn with_architectures(mut self, architectures: ArchitectureConfig) -> Self {
+        let result_cache = self.executor.result_cache().clone();
+        let recorder = self.executor.recorder().clone();
+        let latencies = self.executor.latencies().clone();
+        let state_store = self.executor.state_store().clone();
+        self.executor = Arc::new(
+            DotExecutor::with_result_cache(result_cache)
+                .with_state_store(state_store)
+                .with_recorder(recorder)
+                .with_latencies(latencies)
+                .with_architectures(architectures.clone()),
+        );
+        self.registry = Arc::new(DotRegistry::with_drain_grace_period(self.registry.drain_grace_period()).with_architectures(architectures));
+        self.register_checkpoint_target();
+        self
+    }
+
+    /// Records redeploy checkpoints in `checkpoints`, shared with other services taking checkpoints
+    pub fn with_checkpoints(mut self, checkpoints: Arc<OperationCheckpoints>) -> Self {
+        self.checkpoints = checkpoints;
+        self.register_checkpoint_target();
+        self
+    }
+
+    /// Adds a check every redeploy must pass on top of the built-in ones
+    pub fn with_redeploy_check(mut self, name: &str, check: impl Fn(&SystemState) -> VerificationResult + Send + Sync + 'static, is_critical: bool) -> Self {
+        self.redeploy_verifier.add_verification_check(name, check, is_critical).expect("the verifier accepts checks");
+        self
+    }
+
+    /// Rejects streamed deployments once their bytecode outgrows `max_bytes`
+    pub fn with_max_artifact_size(mut self, max_bytes: u64) -> Self {
+        self.max_artifact_bytes = max_bytes;
+        self
+    }
+
+    /// Checks executions against the ABIs registered with the evaluator's registry,
+    /// e.g. the one the ABI service serves
+    pub fn with_permissions(mut self, permissions: Arc<PermissionEvaluator>) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    pub fn resource_allocator(&self) -> &Arc<ResourceAllocator> {
+        &self.resource_allocator
+    }
+
+    pub fn checkpoints(&self) -> &Arc<OperationCheckpoints> {
+        &self.checkpoints
+    }
+
+    pub fn permissions(&self) -> &Arc<PermissionEvaluator> {
+        &self.permissions
+    }
+
+    pub fn latencies(&self) -> &Arc<LatencyHistograms> {
+        self.executor.latencies()
+    }
+
+    pub fn architectures(&self) -> &ArchitectureConfig {
+        self.registry.architectures()
+    }
+
+    fn register_checkpoint_target(&self) {
+        let target = DotCheckpointTarget::new(self.registry.clone(), self.executor.clone(), self.resource_allocator.clone());
+        self.checkpoints.register_target(CheckpointCategory::DotRedeploy, Arc::new(target));
+    }
+
+    /// Runs under a span continuing the caller's `traceparent`, tagged with the dot and request ids,
+    /// for the principal the gateway forwarded
     pub async fn execute_dot(&self, request: Request<ExecuteDotRequest>) -> TonicResult<Response<ExecuteDotResponse>> {
+        let metadata = request.metadata();
+        let incoming = metadata.get(TRACEPARENT_HEADER).and_then(|value| value.to_str().ok()).and_then(TraceContext::parse);
+        let request_id = metadata
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let context = incoming.map_or_else(TraceContext::new_root, |parent| parent.child());
+        let caller = Caller::from_metadata(metadata);
+
         let req = request.into_inner();
+        let span = info_span!(
+            "execute_dot",
+            dot_id = %req.dot_id,
+            request_id = %request_id,
+            trace_id = %context.trace_id_hex(),
+            span_id = %context.span_id_hex(),
+            parent_span_id = incoming.map(|parent| parent.span_id_hex()),
+        );
+        if let Some(parent) = &incoming {
+            parent.attach_as_parent(&span);
+        }
+
+        self.execute_dot_traced(req, caller).instrument(span).await
+    }
 
+    async fn execute_dot_traced(&self, req: ExecuteDotRequest, caller: Caller) -> TonicResult<Response<ExecuteDotResponse>> {
+        let received = Instant::now();
         info!("Executing dot: {}", req.dot_id);
 
         // Validate request
@@ -69,19 +342,90 @@ impl DotsService {
             return Err(Status::invalid_argument("dot_id cannot be empty"));
         }
 
-        // Get dot from registry
-        let dot_info = self.registry.get_dot(&req.dot_id).await.map_err(|e| Status::not_found(format!("Dot not found: {}", e)))?;
+        // Pin the active version so a concurrent redeploy lets this execution finish on it
+        let lease = self.registry.begin_execution(&req.dot_id).map_err(registry_status)?;
+
+        let operation = if req.operation.is_empty() { DEFAULT_OPERATION } else { req.operation.as_str() };
+        self.permissions.check(&req.dot_id, operation, &caller, &lease.info.owner_id).map_err(|e| match e {
+            e @ PermissionError::Denied { .. } => Status::permission_denied(e.to_string()),
+            e => Status::internal(e.to_string()),
+        })?;
+
+        // The execution holds its share of the dot's quota until it finishes
+        let _allocation = self.allocate_execution(&req.dot_id).await?;
 
         // Execute dot
-        let result = self.executor.execute(&dot_info, req).await.map_err(|e| Status::internal(format!("Execution failed: {}", e)))?;
+        self.executor.latencies().record(&req.dot_id, Latency::QueueWait, received.elapsed());
+        let result = self.executor.execute(&lease, req).await.map_err(|e| match e {
+            e @ (ExecutorError::NonDeterministic { .. } | ExecutorError::NotInteractive(_) | ExecutorError::Architecture(_)) => Status::failed_precondition(e.to_string()),
+            e => Status::internal(format!("Execution failed: {}", e)),
+        })?;
 
         Ok(Response::new(result))
     }
 
+    /// Charge one execution to the dot's quota: an equal share of it per concurrent task allowed
+    async fn allocate_execution(&self, dot_id: &str) -> TonicResult<AllocationGuard<'_>> {
+        let quota = self.resource_allocator.quota(dot_id).map(|status| status.config).unwrap_or_default();
+        let share = quota.max_concurrent_tasks.max(1);
+        let task = Task {
+            id: self.next_task_id.fetch_add(1, Ordering::Relaxed),
+            priority: TaskPriority::default(),
+            resource_requirements: ResourceRequirements {
+                memory_mb: quota.max_memory_mb / share,
+                cpu_cores: quota.max_cpu_cores / share as f32,
+            },
+            dot_id: Some(dot_id.to_string()),
+        };
+        self.resource_allocator.allocate_resources(&task).await.map_err(|e| match e {
+            ExecutionError::QuotaExceeded { dot_id, resource } => Status::resource_exhausted(format!("Dot {dot_id} has no {resource} quota left for another execution")),
+            _ => Status::resource_exhausted(format!("Resources for an execution of dot {dot_id} could not be allocated")),
+        })?;
+
+        Ok(AllocationGuard {
+            allocator: &self.resource_allocator,
+            task_id: task.id,
+        })
+    }
+
+    /// Run a deployed dot under the debugger, driven by the request stream
+    #[instrument(skip(self, request))]
+    pub async fn live_dot_debugging(&self, request: Request<Streaming<DebugRequest>>) -> TonicResult<Response<UnboundedReceiverStream<Result<DebugResponse, Status>>>> {
+        Ok(Response::new(self.debugger.serve(self.registry.clone(), request.into_inner())))
+    }
+
+    /// Run a deployed dot that can pause for client input, driven by the request stream
+    #[instrument(skip(self, request))]
+    pub async fn interactive_dot_execution(
+        &self,
+        request: Request<Streaming<InteractiveExecutionRequest>>,
+    ) -> TonicResult<Response<UnboundedReceiverStream<Result<InteractiveExecutionResponse, Status>>>> {
+        Ok(Response::new(interactive::serve(self.registry.clone(), self.executor.clone(), request.into_inner())))
+    }
+
     #[instrument(skip(self, request))]
     pub async fn deploy_dot(&self, request: Request<DeployDotRequest>) -> TonicResult<Response<DeployDotResponse>> {
-        let req = request.into_inner();
+        let (operation_id, req) = deploy_request(request);
+        self.deploy(req, &operation_id, &mut |_| {}).await.map(Response::new)
+    }
+
+    /// Deploy a dot, streaming each stage as it starts and ends. The last message of a successful
+    /// deployment carries its result; a failed one ends with the failed stage and the error.
+    #[instrument(skip(self, request))]
+    pub fn deploy_dot_with_progress(self: &Arc<Self>, request: Request<DeployDotRequest>) -> TonicResult<Response<UnboundedReceiverStream<Result<DeployDotProgress, Status>>>> {
+        let (operation_id, req) = deploy_request(request);
+        let (tx, rx) = mpsc::unbounded_channel();
+        let service = self.clone();
+        tokio::spawn(async move {
+            let mut progress = DeployProgress { tx, current: None };
+            let result = service.deploy(req, &operation_id, &mut |stage| progress.enter(stage)).await;
+            progress.finish(result);
+        });
+        Ok(Response::new(UnboundedReceiverStream::new(rx)))
+    }
 
+    /// Deploy `req`, calling `enter` as the registry starts each stage
+    async fn deploy(&self, req: DeployDotRequest, operation_id: &str, enter: &mut (dyn FnMut(DeployStage) + Send)) -> TonicResult<DeployDotResponse> {
         info!("Deploying dot: {}", req.dot_name);
 
         // Validate request
@@ -93,8 +437,75 @@ impl DotsService {
             return Err(Status::invalid_argument("dot_source cannot be empty"));
         }
 
+        // A redeploy can be undone: capture the dot as it is before adding the version
+        let checkpoint_id = match self.registry.dot_id_for_name(&req.dot_name) {
+            Some(dot_id) => Some(
+                self.checkpoints
+                    .create(CheckpointCategory::DotRedeploy, operation_id, &dot_id)
+                    .map_err(|e| Status::internal(format!("Failed to checkpoint dot {}: {}", dot_id, e)))?,
+            ),
+            None => None,
+        };
+
         // Deploy dot
-        let result = self.registry.deploy_dot(req).await.map_err(|e| Status::internal(format!("Deployment failed: {}", e)))?;
+        let mut result = self.registry.deploy_dot_observed(req, enter).await.map_err(|e| match e {
+            e @ (RegistryError::InvalidDependency(_) | RegistryError::MissingDependencies { .. } | RegistryError::DependencyCycle(_) | RegistryError::Architecture(_)) => registry_status(e),
+            e => Status::internal(format!("Deployment failed: {}", e)),
+        })?;
+
+        // State and quotas belong to the dot, so only its first version sets them up
+        if result.success && result.version == 1 {
+            // State persisted by an earlier run of the runtime is kept
+            match self.executor.state_store().create_dot(&result.dot_id) {
+                Ok(_) | Err(StateStoreError::DotAlreadyExists(_)) => {}
+                Err(e) => return Err(Status::internal(format!("Failed to initialize dot state: {}", e))),
+            }
+            self.resource_allocator.set_quota(&result.dot_id, QuotaConfig::default());
+        }
+
+        // Results of earlier versions are never served again once a new version is deployed
+        if result.success {
+            self.executor.result_cache().invalidate_dot(&result.dot_id);
+        }
+
+        if let Some(checkpoint_id) = checkpoint_id {
+            self.checkpoints.verify(&checkpoint_id, &self.redeploy_verifier).map_err(|e| match e {
+                RollbackError::VerificationFailed(reason) => Status::aborted(format!(
+                    "Version {} of dot {} failed verification and was rolled back to checkpoint {}: {}",
+                    result.version, result.dot_id, checkpoint_id, reason
+                )),
+                e => Status::internal(format!("Failed to verify redeploy of dot {}: {}", result.dot_id, e)),
+            })?;
+            result.checkpoint_id = checkpoint_id;
+        }
+
+        Ok(result)
+    }
+
+    /// Deploy bytecode streamed in chunks, for artifacts too large for a single message
+    #[instrument(skip(self, request))]
+    pub async fn deploy_dot_stream(&self, request: Request<Streaming<DeployDotChunk>>) -> TonicResult<Response<DeployDotResponse>> {
+        let (metadata, extensions, chunks) = request.into_parts();
+        let req = upload::assemble(chunks, self.max_artifact_bytes).await?;
+
+        self.deploy_dot(Request::from_parts(metadata, extensions, req)).await
+    }
+
+    /// Route new executions to a deployed version, or roll back to the previously active one
+    #[instrument(skip(self, request))]
+    pub async fn activate_dot_version(&self, request: Request<ActivateDotVersionRequest>) -> TonicResult<Response<ActivateDotVersionResponse>> {
+        let req = request.into_inner();
+
+        info!("Activating version {} of dot: {} (rollback: {})", req.version, req.dot_id, req.rollback);
+
+        if req.dot_id.is_empty() {
+            return Err(Status::invalid_argument("dot_id cannot be empty"));
+        }
+        if req.rollback == (req.version != 0) {
+            return Err(Status::invalid_argument("specify either a version or rollback"));
+        }
+
+        let result = self.registry.activate_version(req).await.map_err(registry_status)?;
 
         Ok(Response::new(result))
     }
@@ -116,7 +527,23 @@ impl DotsService {
 
         info!("Deleting dot: {}", req.dot_id);
 
-        let result = self.registry.delete_dot(req).await.map_err(|e| Status::internal(format!("Failed to delete dot: {}", e)))?;
+        let dot_id = req.dot_id.clone();
+        let result = self.registry.delete_dot(req).await.map_err(|e| match e {
+            e @ (RegistryError::DotNotFound(_) | RegistryError::HasDependents { .. }) => registry_status(e),
+            e => Status::internal(format!("Failed to delete dot: {}", e)),
+        })?;
+
+        if result.success {
+            // ParaDots cannot outlive their parent; killing them first releases what they hold of its quota
+            let orphans = self.paradots.terminate_children(&dot_id).await;
+            if orphans > 0 {
+                info!("Terminated {} ParaDots of deleted dot {}", orphans, dot_id);
+            }
+            self.executor.state_store().remove_dot(&dot_id);
+            self.resource_allocator.remove_quota(&dot_id);
+            self.executor.result_cache().remove_dot(&dot_id);
+            self.permissions.invalidate(&dot_id);
+        }
 
         Ok(Response::new(result))
     }
@@ -127,8 +554,1034 @@ impl DotsService {
 
         info!("Getting state for dot: {}", req.dot_id);
 
-        let result = self.executor.get_state(req).await.map_err(|e| Status::internal(format!("Failed to get state: {}", e)))?;
+        if req.dot_id.is_empty() {
+            return Err(Status::invalid_argument("dot_id cannot be empty"));
+        }
+
+        // Unknown dots and pruned versions get distinct codes so clients can tell them apart
+        let result = self.executor.get_state(req).await.map_err(|e| match e {
+            ExecutorError::InvalidInput(msg) => Status::invalid_argument(msg),
+            ExecutorError::StateStore(e @ (StateStoreError::DotNotFound(_) | StateStoreError::VersionNotFound { .. })) => Status::not_found(e.to_string()),
+            ExecutorError::StateStore(e @ StateStoreError::VersionPruned { .. }) => Status::failed_precondition(e.to_string()),
+            e => Status::internal(format!("Failed to get state: {}", e)),
+        })?;
+
+        Ok(Response::new(result))
+    }
+
+    #[instrument(skip(self, request))]
+    pub async fn get_state_diff(&self, request: Request<GetStateDiffRequest>) -> TonicResult<Response<GetStateDiffResponse>> {
+        let req = request.into_inner();
+
+        info!("Diffing state for dot: {}", req.dot_id);
+
+        if req.dot_id.is_empty() {
+            return Err(Status::invalid_argument("dot_id cannot be empty"));
+        }
+
+        let result = self.executor.get_state_diff(req).await.map_err(|e| match e {
+            ExecutorError::StateStore(e @ (StateStoreError::DotNotFound(_) | StateStoreError::VersionNotFound { .. })) => Status::not_found(e.to_string()),
+            ExecutorError::StateStore(e @ StateStoreError::VersionPruned { .. }) => Status::failed_precondition(e.to_string()),
+            e => Status::internal(format!("Failed to diff state: {}", e)),
+        })?;
 
         Ok(Response::new(result))
     }
+
+    #[instrument(skip(self, request))]
+    pub async fn set_dot_quota(&self, request: Request<SetDotQuotaRequest>) -> TonicResult<Response<SetDotQuotaResponse>> {
+        let req = request.into_inner();
+
+        info!("Setting quota for dot: {}", req.dot_id);
+
+        if req.dot_id.is_empty() {
+            return Err(Status::invalid_argument("dot_id cannot be empty"));
+        }
+
+        let quota = req.quota.ok_or_else(|| Status::invalid_argument("quota is required"))?;
+        if quota.max_memory_mb == 0 || quota.max_concurrent_tasks == 0 || quota.max_cpu_cores.is_nan() || quota.max_cpu_cores <= 0.0 {
+            return Err(Status::invalid_argument("quota limits must be greater than zero"));
+        }
+
+        self.registry.get_dot(&req.dot_id).await.map_err(|e| Status::not_found(format!("Dot not found: {}", e)))?;
+
+        let config = QuotaConfig {
+            max_memory_mb: quota.max_memory_mb as usize,
+            max_cpu_cores: quota.max_cpu_cores,
+            max_concurrent_tasks: quota.max_concurrent_tasks as usize,
+        };
+        self.resource_allocator.set_quota(&req.dot_id, config);
+
+        Ok(Response::new(SetDotQuotaResponse {
+            success: true,
+            quota: Some(quota),
+            error_message: String::new(),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    pub async fn get_dot_quota(&self, request: Request<GetDotQuotaRequest>) -> TonicResult<Response<GetDotQuotaResponse>> {
+        let req = request.into_inner();
+
+        if req.dot_id.is_empty() {
+            return Err(Status::invalid_argument("dot_id cannot be empty"));
+        }
+
+        let status = self
+            .resource_allocator
+            .quota(&req.dot_id)
+            .ok_or_else(|| Status::not_found(format!("No quota registered for dot: {}", req.dot_id)))?;
+
+        Ok(Response::new(quota_response(status)))
+    }
+
+    /// Spawn a ParaDot under a deployed dot, with limits inherited from or capped by the dot's quota
+    #[instrument(skip(self, request))]
+    pub async fn spawn_paradot(&self, request: Request<SpawnParaDotRequest>) -> TonicResult<Response<SpawnParaDotResponse>> {
+        let req = request.into_inner();
+
+        info!("Spawning ParaDot {} under dot: {}", req.name, req.parent_dot_id);
+
+        if req.parent_dot_id.is_empty() {
+            return Err(Status::invalid_argument("parent_dot_id cannot be empty"));
+        }
+        let source = match req.source {
+            Some(spawn_para_dot_request::Source::Template(template)) if !template.is_empty() => ParaDotSource::Template(template),
+            Some(spawn_para_dot_request::Source::Bytecode(bytecode)) if !bytecode.is_empty() => ParaDotSource::Bytecode(bytecode),
+            _ => return Err(Status::invalid_argument("a template or bytecode is required")),
+        };
+
+        self.registry.get_dot(&req.parent_dot_id).await.map_err(registry_status)?;
+
+        // Unset limits are inherited from the parent's quota
+        let limits = req.limits.unwrap_or_default();
+        let spec = ParaDotSpec {
+            parent_dot_id: req.parent_dot_id,
+            name: req.name,
+            source,
+            limits: QuotaConfig {
+                max_memory_mb: limits.max_memory_mb as usize,
+                max_cpu_cores: limits.max_cpu_cores,
+                max_concurrent_tasks: limits.max_concurrent_tasks as usize,
+            },
+        };
+        let paradot = self.paradots.spawn(spec).map_err(paradot_status)?;
+
+        Ok(Response::new(SpawnParaDotResponse {
+            success: true,
+            paradot: Some(paradot),
+            error_message: String::new(),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    pub async fn list_paradots(&self, request: Request<ListParaDotsRequest>) -> TonicResult<Response<ListParaDotsResponse>> {
+        let filter = request.into_inner().filter.unwrap_or_default();
+        let query = ParaDotQuery {
+            parent_dot_id: Some(filter.parent_dot_id).filter(|id| !id.is_empty()),
+            paradot_type: Some(filter.paradot_type).filter(|paradot_type| !paradot_type.is_empty()),
+            capabilities: filter.capabilities,
+            name_pattern: Some(filter.name_pattern).filter(|pattern| !pattern.is_empty()),
+        };
+
+        let paradots = self.paradots.list(&query);
+
+        Ok(Response::new(ListParaDotsResponse {
+            total_count: paradots.len() as u32,
+            paradots,
+            next_cursor: String::new(),
+        }))
+    }
+
+    /// Run a ParaDot, charging the execution to its parent dot's quota
+    ///
+    /// Bytecode ParaDots run on the executor like dots do. Templates have no code the VM
+    /// can run yet, so their executions answer `Unimplemented`.
+    #[instrument(skip(self, request))]
+    pub async fn execute_paradot(&self, request: Request<ExecuteParaDotRequest>) -> TonicResult<Response<ExecuteParaDotResponse>> {
+        let req = request.into_inner();
+
+        info!("Executing ParaDot: {}", req.paradot_id);
+
+        if req.paradot_id.is_empty() {
+            return Err(Status::invalid_argument("paradot_id cannot be empty"));
+        }
+
+        let started = std::time::Instant::now();
+        let timeout = Some(Duration::from_millis(req.timeout_ms as u64)).filter(|timeout| !timeout.is_zero());
+
+        let executor = self.executor.clone();
+        let paradot_id = req.paradot_id.clone();
+        let input = req.input_data;
+        let output = self
+            .paradots
+            .execute(&req.paradot_id, timeout, |source| {
+                let bytecode = match source {
+                    ParaDotSource::Bytecode(bytecode) => Ok(bytecode.clone()),
+                    ParaDotSource::Template(template) => Err(Status::unimplemented(format!("Running ParaDot template {template} is not supported yet"))),
+                };
+                async move {
+                    executor.execute_paradot(&paradot_id, bytecode?, input).await.map_err(|e| match e {
+                        e @ ExecutorError::Architecture(_) => Status::failed_precondition(e.to_string()),
+                        e => Status::internal(format!("Execution failed: {}", e)),
+                    })
+                }
+            })
+            .await
+            .map_err(paradot_status)??;
+
+        Ok(Response::new(ExecuteParaDotResponse {
+            success: true,
+            output_data: output,
+            execution_time_ms: started.elapsed().as_millis() as u64,
+            error_message: String::new(),
+        }))
+    }
+
+    /// Stop a ParaDot, draining its executions when graceful and cancelling them otherwise
+    #[instrument(skip(self, request))]
+    pub async fn terminate_paradot(&self, request: Request<TerminateParaDotRequest>) -> TonicResult<Response<TerminateParaDotResponse>> {
+        let req = request.into_inner();
+
+        info!("Terminating ParaDot: {} (graceful: {})", req.paradot_id, req.graceful);
+
+        if req.paradot_id.is_empty() {
+            return Err(Status::invalid_argument("paradot_id cannot be empty"));
+        }
+
+        let cancelled = self.paradots.terminate(&req.paradot_id, req.graceful).await.map_err(paradot_status)?;
+
+        Ok(Response::new(TerminateParaDotResponse {
+            success: true,
+            cancelled_executions: cancelled as u32,
+            error_message: String::new(),
+        }))
+    }
+
+    /// Every spawned ParaDot with its parent dot, grouped by parent
+    pub fn paradots(&self) -> Vec<ParaDotInfo> {
+        self.paradots.list(&ParaDotQuery::default())
+    }
+
+    /// Turn result memoization on or off for a deterministic dot
+    #[instrument(skip(self, request))]
+    pub async fn set_dot_caching(&self, request: Request<SetDotCachingRequest>) -> TonicResult<Response<SetDotCachingResponse>> {
+        let req = request.into_inner();
+
+        info!("Setting result caching for dot {} to {}", req.dot_id, req.enabled);
+
+        if req.dot_id.is_empty() {
+            return Err(Status::invalid_argument("dot_id cannot be empty"));
+        }
+
+        let dot = self.registry.get_dot(&req.dot_id).await.map_err(registry_status)?;
+        if req.enabled && !dot.abi.as_ref().is_some_and(|abi| abi.deterministic) {
+            return Err(Status::failed_precondition(format!("Dot {} is not declared deterministic", req.dot_id)));
+        }
+
+        let evicted = self.executor.result_cache().set_enabled(&req.dot_id, req.enabled);
+
+        Ok(Response::new(SetDotCachingResponse {
+            success: true,
+            dot_id: req.dot_id,
+            enabled: req.enabled,
+            evicted_entries: evicted as u64,
+        }))
+    }
+
+    /// Hit rate and memory use of the result cache, overall and per dot
+    #[instrument(skip(self, request))]
+    pub async fn get_dot_cache_stats(&self, request: Request<GetDotCacheStatsRequest>) -> TonicResult<Response<GetDotCacheStatsResponse>> {
+        let req = request.into_inner();
+        let dot_id = Some(req.dot_id.as_str()).filter(|id| !id.is_empty());
+
+        Ok(Response::new(cache_stats_response(self.executor.result_cache().stats(dot_id))))
+    }
+
+    /// Checkpoints taken around risky operations, newest first
+    #[instrument(skip(self, request))]
+    pub async fn list_checkpoints(&self, request: Request<ListCheckpointsRequest>) -> TonicResult<Response<ListCheckpointsResponse>> {
+        let req = request.into_inner();
+        let category = match req.category.trim() {
+            "" => None,
+            name => Some(CheckpointCategory::parse(name).ok_or_else(|| Status::invalid_argument(format!("Unknown checkpoint category: {}", name)))?),
+        };
+
+        Ok(Response::new(ListCheckpointsResponse {
+            checkpoints: self.checkpoints.list(category).iter().map(checkpoint_info).collect(),
+        }))
+    }
+
+    /// Put back the state a checkpoint captured, whichever service took it
+    #[instrument(skip(self, request))]
+    pub async fn restore_checkpoint(&self, request: Request<RestoreCheckpointRequest>) -> TonicResult<Response<RestoreCheckpointResponse>> {
+        let req = request.into_inner();
+
+        info!("Restoring checkpoint: {}", req.checkpoint_id);
+
+        if req.checkpoint_id.is_empty() {
+            return Err(Status::invalid_argument("checkpoint_id cannot be empty"));
+        }
+
+        // Restoring storage settings restarts the flusher, so keep it off the async workers
+        let checkpoints = self.checkpoints.clone();
+        let restored = tokio::task::spawn_blocking(move || checkpoints.restore(&req.checkpoint_id))
+            .await
+            .map_err(|e| Status::internal(format!("Checkpoint restore failed: {}", e)))?
+            .map_err(|e| match e {
+                RollbackError::CheckpointNotFound(_) => Status::not_found(e.to_string()),
+                e => Status::internal(e.to_string()),
+            })?;
+
+        Ok(Response::new(RestoreCheckpointResponse {
+            checkpoint: Some(checkpoint_info(&restored)),
+        }))
+    }
+}
+
+/// The operation id a deployment is checkpointed under, and the request with the principal the
+/// gateway authenticated as its deployer, who owns the dot if it is new
+fn deploy_request(request: Request<DeployDotRequest>) -> (String, DeployDotRequest) {
+    let operation_id = request
+        .metadata()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let principal = Caller::from_metadata(request.metadata()).principal;
+    let mut req = request.into_inner();
+    if let Some(principal) = principal {
+        req.deployer_id = principal;
+    }
+    (operation_id, req)
+}
+
+/// Sends the stages of a deployment to a progress stream as they start and end
+struct DeployProgress {
+    tx: mpsc::UnboundedSender<Result<DeployDotProgress, Status>>,
+    current: Option<(DeployStage, Instant)>,
+}
+
+impl DeployProgress {
+    fn send(&self, stage: DeployStage, state: DeployStageState, duration_ms: u64, message: String, result: Option<DeployDotResponse>) {
+        // A client that stopped listening does not stop the deployment
+        let _ = self.tx.send(Ok(DeployDotProgress {
+            stage: stage.name().to_string(),
+            state: state as i32,
+            duration_ms,
+            message,
+            result,
+        }));
+    }
+
+    fn enter(&mut self, stage: DeployStage) {
+        self.end(DeployStageState::Completed, String::new(), None);
+        self.send(stage, DeployStageState::Running, 0, String::new(), None);
+        self.current = Some((stage, Instant::now()));
+    }
+
+    fn end(&mut self, state: DeployStageState, message: String, result: Option<DeployDotResponse>) {
+        if let Some((stage, started)) = self.current.take() {
+            self.send(stage, state, started.elapsed().as_millis() as u64, message, result);
+        }
+    }
+
+    fn finish(mut self, result: TonicResult<DeployDotResponse>) {
+        match result {
+            Ok(response) if response.activated => self.end(DeployStageState::Completed, String::new(), Some(response)),
+            Ok(response) => {
+                self.end(DeployStageState::Completed, String::new(), None);
+                self.send(DeployStage::Activation, DeployStageState::Skipped, 0, "version was only staged".to_string(), Some(response));
+            }
+            Err(status) => {
+                // A request rejected before the registry looked at it failed validation
+                let (stage, started) = self.current.take().unwrap_or((DeployStage::Validation, Instant::now()));
+                self.send(stage, DeployStageState::Failed, started.elapsed().as_millis() as u64, status.message().to_string(), None);
+                let _ = self.tx.send(Err(status));
+            }
+        }
+    }
+}
+
+fn checkpoint_info(checkpoint: &OperationCheckpoint) -> OperationCheckpointInfo {
+    OperationCheckpointInfo {
+        checkpoint_id: checkpoint.checkpoint.id.clone(),
+        operation_id: checkpoint.operation_id.clone(),
+        category: checkpoint.category.to_string(),
+        subject: checkpoint.subject.clone(),
+        created_at: checkpoint.checkpoint.timestamp / 1_000_000_000,
+        status: checkpoint.status.as_str().to_string(),
+        failure: checkpoint.failure.clone().unwrap_or_default(),
+    }
+}
+
+fn cache_stats_response(stats: ResultCacheStats) -> GetDotCacheStatsResponse {
+    GetDotCacheStatsResponse {
+        capacity_bytes: stats.capacity_bytes as u64,
+        bytes: stats.bytes as u64,
+        entries: stats.entries as u64,
+        evictions: stats.evictions,
+        hits: stats.hits,
+        misses: stats.misses,
+        hit_rate: stats.hit_rate(),
+        dots: stats
+            .dots
+            .into_iter()
+            .map(|dot| DotCacheStats {
+                hit_rate: dot.hit_rate(),
+                dot_id: dot.dot_id,
+                enabled: dot.enabled,
+                hits: dot.hits,
+                misses: dot.misses,
+                bypasses: dot.bypasses,
+                entries: dot.entries as u64,
+                bytes: dot.bytes as u64,
+            })
+            .collect(),
+    }
+}
+
+fn quota_response(status: DotQuotaStatus) -> GetDotQuotaResponse {
+    GetDotQuotaResponse {
+        dot_id: status.dot_id,
+        quota: Some(DotQuota {
+            max_memory_mb: status.config.max_memory_mb as u64,
+            max_cpu_cores: status.config.max_cpu_cores,
+            max_concurrent_tasks: status.config.max_concurrent_tasks as u32,
+        }),
+        usage: Some(DotQuotaUsage {
+            memory_mb: status.usage.memory_mb as u64,
+            cpu_cores: status.usage.cpu_cores,
+            active_tasks: status.usage.active_tasks as u32,
+        }),
+        rejections: status.rejections,
+    }
+}
+
+fn paradot_status(error: ParaDotError) -> Status {
+    match error {
+        ParaDotError::NotFound(_) => Status::not_found(error.to_string()),
+        ParaDotError::UnknownTemplate(_) => Status::invalid_argument(error.to_string()),
+        ParaDotError::Terminating(_) => Status::failed_precondition(error.to_string()),
+        ParaDotError::ConcurrencyLimit { .. } | ParaDotError::QuotaExceeded { .. } | ParaDotError::AllocationFailed(_) => Status::resource_exhausted(error.to_string()),
+        ParaDotError::Cancelled(_) => Status::cancelled(error.to_string()),
+        ParaDotError::Timeout { .. } => Status::deadline_exceeded(error.to_string()),
+    }
+}
+
+fn registry_status(error: RegistryError) -> Status {
+    match error {
+        RegistryError::DotNotFound(_) | RegistryError::VersionNotFound { .. } => Status::not_found(error.to_string()),
+        RegistryError::NoActiveVersion(_)
+        | RegistryError::NoPreviousVersion(_)
+        | RegistryError::MissingDependencies { .. }
+        | RegistryError::DependencyCycle(_)
+        | RegistryError::HasDependents { .. }
+        | RegistryError::Architecture(ArchitectureError::Unsupported { .. }) => Status::failed_precondition(error.to_string()),
+        RegistryError::InvalidDependency(_) | RegistryError::Architecture(ArchitectureError::InvalidTarget(_)) => Status::invalid_argument(error.to_string()),
+        error => Status::internal(error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::vm_service::{DotAbi, OperationPermission, ParaDotFilter, PermissionConfig, RegisterAbiRequest};
+    use dotvm_common::telemetry::PRINCIPAL_HEADER;
+    use std::collections::HashMap;
+    use std::fmt::Debug;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tracing::Subscriber;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing_subscriber::Layer;
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::registry::LookupSpan;
+
+    #[derive(Debug)]
+    struct RecordedSpan {
+        name: &'static str,
+        parent: Option<usize>,
+        fields: HashMap<String, String>,
+    }
+
+    /// Index of a span in [`SpanRecorder::spans`], stored in the span's extensions
+    struct SpanIndex(usize);
+
+    /// Records every span with its parent and fields
+    #[derive(Clone, Default)]
+    struct SpanRecorder {
+        spans: Arc<Mutex<Vec<RecordedSpan>>>,
+    }
+
+    struct FieldVisitor<'a>(&'a mut HashMap<String, String>);
+
+    impl Visit for FieldVisitor<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0.insert(field.name().to_string(), format!("{value:?}"));
+        }
+    }
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for SpanRecorder {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            let span = ctx.span(id).unwrap();
+            let parent = span.parent().and_then(|parent| parent.extensions().get::<SpanIndex>().map(|index| index.0));
+            let mut fields = HashMap::new();
+            attrs.record(&mut FieldVisitor(&mut fields));
+
+            let mut spans = self.spans.lock().unwrap();
+            span.extensions_mut().insert(SpanIndex(spans.len()));
+            spans.push(RecordedSpan {
+                name: attrs.metadata().name(),
+                parent,
+                fields,
+            });
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+            let span = ctx.span(id).unwrap();
+            let extensions = span.extensions();
+            let index = extensions.get::<SpanIndex>().unwrap().0;
+            values.record(&mut FieldVisitor(&mut self.spans.lock().unwrap()[index].fields));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_dot_produces_span_tree() {
+        let recorder = SpanRecorder::default();
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.clone()));
+
+        let service = DotsService::new();
+        let deployed = service
+            .deploy_dot(Request::new(DeployDotRequest {
+                dot_name: "counter".to_string(),
+                dot_source: "dot counter {}".to_string(),
+                ..Default::default()
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let mut request = Request::new(ExecuteDotRequest {
+            dot_id: deployed.dot_id.clone(),
+            ..Default::default()
+        });
+        request
+            .metadata_mut()
+            .insert(TRACEPARENT_HEADER, "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".parse().unwrap());
+        request.metadata_mut().insert(REQUEST_ID_HEADER, "req-42".parse().unwrap());
+        service.execute_dot(request).await.unwrap();
+
+        let spans = recorder.spans.lock().unwrap();
+        let children = |parent: usize| -> Vec<usize> { (0..spans.len()).filter(|&i| spans[i].parent == Some(parent)).collect() };
+        let names = |indices: &[usize]| -> Vec<&str> { indices.iter().map(|&i| spans[i].name).collect() };
+
+        // The request span continues the caller's trace and carries the searchable ids
+        let root = spans.iter().position(|span| span.name == "execute_dot").unwrap();
+        let fields = &spans[root].fields;
+        assert_eq!(spans[root].parent, None);
+        assert_eq!(fields["dot_id"], deployed.dot_id);
+        assert_eq!(fields["request_id"], "req-42");
+        assert_eq!(fields["trace_id"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(fields["parent_span_id"], "00f067aa0ba902b7");
+        assert_ne!(fields["span_id"], "00f067aa0ba902b7");
+
+        let execute = children(root);
+        assert_eq!(names(&execute), vec!["execute"]);
+        let stages = children(execute[0]);
+        assert_eq!(names(&stages), vec!["validation", "state_access", "execution", "validation"]);
+        assert_eq!(spans[stages[0]].fields["stage"], "inputs");
+        assert_eq!(spans[stages[3]].fields["stage"], "outputs");
+
+        // Storage work done for the request hangs below the stage that needed it
+        let storage = children(stages[1]);
+        assert_eq!(names(&storage), vec!["dotdb"]);
+        assert_eq!(spans[storage[0]].fields["dot_id"], deployed.dot_id);
+        assert!(spans[storage[0]].fields.contains_key("pages_read"));
+    }
+
+    #[tokio::test]
+    async fn test_deterministic_dot_results_are_memoized() {
+        let service = DotsService::new();
+        let deploy = |source: &str| {
+            Request::new(DeployDotRequest {
+                dot_name: "pricing".to_string(),
+                dot_source: source.to_string(),
+                metadata: Some(DotMetadata {
+                    deterministic: true,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+        };
+        let dot_id = service.deploy_dot(deploy("v1")).await.unwrap().into_inner().dot_id;
+        let execute = |bypass_cache: bool| {
+            let request = Request::new(ExecuteDotRequest {
+                dot_id: dot_id.clone(),
+                inputs: HashMap::from([("order".to_string(), br#"{"sku": "a", "qty": 2}"#.to_vec())]),
+                options: Some(crate::proto::vm_service::ExecutionOptions { bypass_cache, ..Default::default() }),
+                ..Default::default()
+            });
+            async { service.execute_dot(request).await.unwrap().into_inner() }
+        };
+
+        assert!(!execute(false).await.cached);
+        let cached = execute(false).await;
+        assert!(cached.cached);
+        assert_eq!(cached.outputs["order"], br#"{"sku": "a", "qty": 2}"#);
+        assert!(!execute(true).await.cached);
+
+        let stats = |dot_id: &str| {
+            let request = Request::new(GetDotCacheStatsRequest { dot_id: dot_id.to_string() });
+            async { service.get_dot_cache_stats(request).await.unwrap().into_inner() }
+        };
+        let dot = stats(&dot_id).await.dots.remove(0);
+        assert_eq!((dot.hits, dot.misses, dot.bypasses, dot.entries), (1, 1, 1, 1));
+        assert_eq!(dot.hit_rate, 0.5);
+
+        // A redeploy serves the new version, so the old results are dropped
+        service.deploy_dot(deploy("v2")).await.unwrap();
+        assert_eq!(stats(&dot_id).await.entries, 0);
+        let executed = execute(false).await;
+        assert!(!executed.cached);
+        assert_eq!(executed.dot_version, 2);
+
+        let disabled = service
+            .set_dot_caching(Request::new(SetDotCachingRequest {
+                dot_id: dot_id.clone(),
+                enabled: false,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(disabled.evicted_entries, 1);
+        assert!(!execute(false).await.cached);
+        assert!(!stats(&dot_id).await.dots[0].enabled);
+    }
+
+    #[tokio::test]
+    async fn test_caching_requires_a_deterministic_dot() {
+        let service = DotsService::new();
+        let deployed = service
+            .deploy_dot(Request::new(DeployDotRequest {
+                dot_name: "clock".to_string(),
+                dot_source: "dot clock {}".to_string(),
+                ..Default::default()
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let status = service
+            .set_dot_caching(Request::new(SetDotCachingRequest {
+                dot_id: deployed.dot_id.clone(),
+                enabled: true,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+        // Non-deterministic dots run every time and never touch the cache
+        let request = || {
+            Request::new(ExecuteDotRequest {
+                dot_id: deployed.dot_id.clone(),
+                ..Default::default()
+            })
+        };
+        service.execute_dot(request()).await.unwrap();
+        assert!(!service.execute_dot(request()).await.unwrap().into_inner().cached);
+        let stats = service.get_dot_cache_stats(Request::new(GetDotCacheStatsRequest::default())).await.unwrap();
+        assert!(stats.into_inner().dots.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_executions_are_charged_to_the_dots_quota() {
+        let service = DotsService::new();
+        let deployed = service
+            .deploy_dot(Request::new(DeployDotRequest {
+                dot_name: "metered".to_string(),
+                dot_source: "dot metered {}".to_string(),
+                ..Default::default()
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        let request = || {
+            Request::new(ExecuteDotRequest {
+                dot_id: deployed.dot_id.clone(),
+                ..Default::default()
+            })
+        };
+
+        // Another task holding the dot's only slot leaves no quota for an execution
+        service.resource_allocator().set_quota(
+            &deployed.dot_id,
+            QuotaConfig {
+                max_memory_mb: 512,
+                max_cpu_cores: 1.0,
+                max_concurrent_tasks: 1,
+            },
+        );
+        let holder = Task {
+            id: 1,
+            priority: TaskPriority::default(),
+            resource_requirements: ResourceRequirements { memory_mb: 1, cpu_cores: 0.1 },
+            dot_id: Some(deployed.dot_id.clone()),
+        };
+        service.resource_allocator().allocate_resources(&holder).await.unwrap();
+        let status = service.execute_dot(request()).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert!(status.message().contains("concurrent"), "{}", status.message());
+
+        service.resource_allocator().release(holder.id);
+        service.execute_dot(request()).await.unwrap();
+        // The finished execution gave its share back
+        let usage = service.resource_allocator().quota(&deployed.dot_id).unwrap().usage;
+        assert_eq!((usage.active_tasks, usage.memory_mb), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_failed_redeploy_is_rolled_back() {
+        let reject = Arc::new(AtomicBool::new(false));
+        let rejecting = reject.clone();
+        let service = DotsService::new().with_redeploy_check(
+            "smoke_test",
+            move |_| {
+                if rejecting.load(Ordering::SeqCst) {
+                    VerificationResult::Invalid("smoke test failed".to_string())
+                } else {
+                    VerificationResult::Valid
+                }
+            },
+            true,
+        );
+        let deploy = |source: &str| {
+            Request::new(DeployDotRequest {
+                dot_name: "pricing".to_string(),
+                dot_source: source.to_string(),
+                ..Default::default()
+            })
+        };
+        let executed_version = |dot_id: String| {
+            let request = Request::new(ExecuteDotRequest { dot_id, ..Default::default() });
+            async { service.execute_dot(request).await.unwrap().into_inner().dot_version }
+        };
+
+        let first = service.deploy_dot(deploy("v1")).await.unwrap().into_inner();
+        assert!(first.checkpoint_id.is_empty());
+        let dot_id = first.dot_id;
+
+        reject.store(true, Ordering::SeqCst);
+        let status = service.deploy_dot(deploy("v2")).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Aborted);
+        assert!(status.message().contains("smoke test failed"));
+
+        // Version 2 is gone and executions still go to version 1
+        let dots = service.list_dots(Request::new(ListDotsRequest::default())).await.unwrap().into_inner().dots;
+        assert_eq!(dots[0].active_version, 1);
+        assert_eq!(dots[0].versions.len(), 1);
+        assert_eq!(executed_version(dot_id.clone()).await, 1);
+
+        let list = |category: &str| {
+            let request = Request::new(ListCheckpointsRequest { category: category.to_string() });
+            async { service.list_checkpoints(request).await.map(|response| response.into_inner().checkpoints) }
+        };
+        let checkpoints = list("dot_redeploy").await.unwrap();
+        assert_eq!(checkpoints.len(), 1);
+        assert_eq!(checkpoints[0].subject, dot_id);
+        assert_eq!(checkpoints[0].status, "rolled_back");
+        assert!(checkpoints[0].failure.contains("smoke test failed"));
+        assert!(list("config_migration").await.unwrap().is_empty());
+        assert_eq!(list("nonsense").await.unwrap_err().code(), tonic::Code::InvalidArgument);
+
+        // A redeploy that passes is kept, and its checkpoint can still be restored by hand
+        reject.store(false, Ordering::SeqCst);
+        let redeployed = service.deploy_dot(deploy("v3")).await.unwrap().into_inner();
+        assert_eq!(redeployed.version, 3);
+        assert_eq!(executed_version(dot_id.clone()).await, 3);
+
+        let restored = service
+            .restore_checkpoint(Request::new(RestoreCheckpointRequest {
+                checkpoint_id: redeployed.checkpoint_id,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(restored.checkpoint.unwrap().status, "restored");
+        assert_eq!(executed_version(dot_id).await, 1);
+
+        let status = service
+            .restore_checkpoint(Request::new(RestoreCheckpointRequest {
+                checkpoint_id: "checkpoint-0".to_string(),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_deploy_progress_reports_each_stage() {
+        use crate::proto::vm_service::DeploymentOptions;
+        use tokio_stream::StreamExt;
+
+        let service = Arc::new(DotsService::new());
+        let deploy = |name: &str, stage_only: bool| {
+            let request = Request::new(DeployDotRequest {
+                dot_name: name.to_string(),
+                dot_source: "dot".to_string(),
+                options: Some(DeploymentOptions { stage_only, ..Default::default() }),
+                ..Default::default()
+            });
+            let stream = service.deploy_dot_with_progress(request).unwrap().into_inner();
+            stream.collect::<Vec<_>>()
+        };
+        let stages = |updates: &[Result<DeployDotProgress, Status>]| -> Vec<(String, DeployStageState)> {
+            updates.iter().filter_map(|update| update.as_ref().ok()).map(|update| (update.stage.clone(), update.state())).collect()
+        };
+        let ran = |stage: &str| [(stage.to_string(), DeployStageState::Running), (stage.to_string(), DeployStageState::Completed)];
+
+        let updates = deploy("pricing", false).await;
+        let expected: Vec<_> = ["validation", "transpilation", "registration", "activation"].into_iter().flat_map(ran).collect();
+        assert_eq!(stages(&updates), expected);
+        let result = updates.last().unwrap().as_ref().unwrap().result.clone().unwrap();
+        assert!(result.activated);
+        assert_eq!(result.version, 1);
+
+        // Staging stops after registration
+        let updates = deploy("pricing", true).await;
+        let mut expected: Vec<_> = ["validation", "transpilation", "registration"].into_iter().flat_map(ran).collect();
+        expected.push(("activation".to_string(), DeployStageState::Skipped));
+        assert_eq!(stages(&updates), expected);
+        assert_eq!(updates.last().unwrap().as_ref().unwrap().result.as_ref().unwrap().version, 2);
+
+        // A failure names its stage, then ends the stream with the error
+        let updates = deploy("", false).await;
+        assert_eq!(stages(&updates), [("validation".to_string(), DeployStageState::Failed)]);
+        assert_eq!(updates[0].as_ref().unwrap().message, "dot_name cannot be empty");
+        assert_eq!(updates[1].as_ref().unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_paradot_executions_run_and_are_charged_to_the_parent() {
+        let service = DotsService::new();
+        let parent = service
+            .deploy_dot(Request::new(DeployDotRequest {
+                dot_name: "pipeline".to_string(),
+                dot_source: "dot pipeline {}".to_string(),
+                ..Default::default()
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .dot_id;
+        service.resource_allocator().set_quota(
+            &parent,
+            QuotaConfig {
+                max_memory_mb: 512,
+                max_cpu_cores: 1.0,
+                max_concurrent_tasks: 1,
+            },
+        );
+        let paradot = service
+            .spawn_paradot(Request::new(SpawnParaDotRequest {
+                parent_dot_id: parent.clone(),
+                source: Some(spawn_para_dot_request::Source::Bytecode(vec![0xD0, 0x7D])),
+                ..Default::default()
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .paradot
+            .unwrap();
+        let request = || {
+            Request::new(ExecuteParaDotRequest {
+                paradot_id: paradot.paradot_id.clone(),
+                input_data: b"ping".to_vec(),
+                ..Default::default()
+            })
+        };
+
+        // A task holding the parent's only slot leaves no quota for the ParaDot
+        let holder = Task {
+            id: 1,
+            priority: TaskPriority::default(),
+            resource_requirements: ResourceRequirements { memory_mb: 1, cpu_cores: 0.1 },
+            dot_id: Some(parent.clone()),
+        };
+        service.resource_allocator().allocate_resources(&holder).await.unwrap();
+        let status = service.execute_paradot(request()).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+
+        service.resource_allocator().release(holder.id);
+        let executed = service.execute_paradot(request()).await.unwrap().into_inner();
+        assert!(executed.success);
+        assert_eq!(executed.output_data, b"ping");
+        // The finished execution gave the parent's share back
+        let usage = service.resource_allocator().quota(&parent).unwrap().usage;
+        assert_eq!((usage.active_tasks, usage.memory_mb), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_deleting_a_parent_terminates_running_paradots() {
+        let service = Arc::new(DotsService::new());
+        let deploy = |name: &str| {
+            let request = Request::new(DeployDotRequest {
+                dot_name: name.to_string(),
+                dot_source: format!("dot {name} {{}}"),
+                ..Default::default()
+            });
+            let service = service.clone();
+            async move { service.deploy_dot(request).await.unwrap().into_inner().dot_id }
+        };
+        let parent = deploy("pipeline").await;
+        let sibling = deploy("reporting").await;
+
+        let spawn = |parent_dot_id: &str, source: spawn_para_dot_request::Source| {
+            let request = Request::new(SpawnParaDotRequest {
+                parent_dot_id: parent_dot_id.to_string(),
+                source: Some(source),
+                ..Default::default()
+            });
+            let service = service.clone();
+            async move { service.spawn_paradot(request).await.map(|response| response.into_inner().paradot.unwrap()) }
+        };
+        let worker = spawn(&parent, spawn_para_dot_request::Source::Template("DataProcessor".to_string())).await.unwrap();
+        spawn(&parent, spawn_para_dot_request::Source::Bytecode(vec![0xD0, 0x7D])).await.unwrap();
+        let survivor = spawn(&sibling, spawn_para_dot_request::Source::Template("NetworkIO".to_string())).await.unwrap();
+        assert_eq!(worker.parent_dot_id, parent);
+
+        // An execution that only ends when it is cancelled
+        let running = {
+            let service = service.clone();
+            let paradot_id = worker.paradot_id.clone();
+            tokio::spawn(async move { service.paradots.execute(&paradot_id, None, |_| std::future::pending::<()>()).await })
+        };
+        while service.resource_allocator.quota(&parent).unwrap().usage.active_tasks == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        let deleted = service
+            .delete_dot(Request::new(DeleteDotRequest {
+                dot_id: parent.clone(),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+        assert!(deleted.into_inner().success);
+        assert!(matches!(running.await.unwrap(), Err(ParaDotError::Cancelled(_))));
+
+        // Only the sibling's ParaDot is left, and it still runs
+        let remaining = service.paradots();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].paradot_id, survivor.paradot_id);
+        let orphans = service
+            .list_paradots(Request::new(ListParaDotsRequest {
+                filter: Some(ParaDotFilter {
+                    parent_dot_id: parent.clone(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+        assert_eq!(orphans.into_inner().total_count, 0);
+
+        // It is admitted to run, but template code cannot be executed yet
+        let executed = service
+            .execute_paradot(Request::new(ExecuteParaDotRequest {
+                paradot_id: survivor.paradot_id,
+                input_data: b"ping".to_vec(),
+                ..Default::default()
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(executed.code(), tonic::Code::Unimplemented);
+        let status = spawn(&parent, spawn_para_dot_request::Source::Template("DataProcessor".to_string())).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_executions_follow_the_permissions_of_the_latest_abi() {
+        let abis = Arc::new(AbiRegistry::new());
+        let service = DotsService::new().with_permissions(PermissionEvaluator::new(abis.clone()));
+        let deploy = |source: &str| {
+            let mut request = Request::new(DeployDotRequest {
+                dot_name: "vault".to_string(),
+                dot_source: source.to_string(),
+                deployer_id: "api-gateway".to_string(),
+                ..Default::default()
+            });
+            request.metadata_mut().insert(PRINCIPAL_HEADER, "alice".parse().unwrap());
+            request
+        };
+        let dot_id = service.deploy_dot(deploy("v1")).await.unwrap().into_inner().dot_id;
+
+        let register = |version: &str, permissions: PermissionConfig| {
+            let request = RegisterAbiRequest {
+                dot_id: dot_id.clone(),
+                abi: Some(DotAbi {
+                    dot_name: "vault".to_string(),
+                    version: version.to_string(),
+                    description: "Vault dot".to_string(),
+                    permissions: Some(permissions),
+                    ..Default::default()
+                }),
+                registrar_id: "alice".to_string(),
+            };
+            let abis = abis.clone();
+            async move { assert!(abis.register_abi(request).await.unwrap().success) }
+        };
+        let execute = |operation: &str, principal: Option<&str>| {
+            let mut request = Request::new(ExecuteDotRequest {
+                dot_id: dot_id.clone(),
+                operation: operation.to_string(),
+                ..Default::default()
+            });
+            if let Some(principal) = principal {
+                request.metadata_mut().insert(PRINCIPAL_HEADER, principal.parse().unwrap());
+            }
+            async { service.execute_dot(request).await.map(|_| ()).map_err(|status| status.code()) }
+        };
+
+        register(
+            "1.0.0",
+            PermissionConfig {
+                public_operations: vec!["withdraw".to_string()],
+                owner_operations: vec!["close".to_string()],
+                ..Default::default()
+            },
+        )
+        .await;
+        assert_eq!(execute("withdraw", None).await, Ok(()));
+        // The forwarded deployer owns the dot, not the deployer id in the request
+        assert_eq!(execute("close", Some("bob")).await, Err(tonic::Code::PermissionDenied));
+        assert_eq!(execute("close", Some("alice")).await, Ok(()));
+        // Neither the entry point nor unknown operations are declared
+        assert_eq!(execute("", Some("alice")).await, Err(tonic::Code::PermissionDenied));
+        assert_eq!(execute("drain", Some("alice")).await, Err(tonic::Code::PermissionDenied));
+
+        // The redeploy puts withdrawals behind authentication, which the cached permissions must not hide
+        service.deploy_dot(deploy("v2")).await.unwrap();
+        register(
+            "2.0.0",
+            PermissionConfig {
+                protected_operations: HashMap::from([("withdraw".to_string(), OperationPermission::default())]),
+                owner_operations: vec!["close".to_string()],
+                ..Default::default()
+            },
+        )
+        .await;
+        assert_eq!(execute("withdraw", None).await, Err(tonic::Code::PermissionDenied));
+        assert_eq!(execute("withdraw", Some("bob")).await, Ok(()));
+
+        let denials = service.permissions().denials();
+        let audited: Vec<(Option<&str>, &str)> = denials.iter().map(|denial| (denial.principal.as_deref(), denial.operation.as_str())).collect();
+        assert_eq!(audited, vec![(Some("bob"), "close"), (Some("alice"), DEFAULT_OPERATION), (Some("alice"), "drain"), (None, "withdraw")]);
+        assert!(denials.iter().all(|denial| denial.dot_id == dot_id));
+    }
 }
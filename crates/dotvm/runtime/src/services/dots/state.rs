@@ -0,0 +1,789 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Dot state store - versioned contract state backed by a Merkle Patricia Trie
+//!
+//! Each dot has its own trie. Trie nodes are never removed, so every committed
+//! root stays readable for as long as its version is retained by the
+//! [`DotVersionManager`].
+//!
+//! Nodes live in a dotdb database next to a record per dot of its retained
+//! versions, so a store opened on the same database picks up where the last
+//! one stopped.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+use dotdb_core::state::db_interface::DatabaseInterface;
+use dotdb_core::state::mpt::{DiffOptions, ScanOptions, TrieChange};
+use dotdb_core::state::{Database, DotAddress, DotStateVersion, DotVersionManager, MerklePatriciaTrie, MptStorageAdapter, StateVersionId};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tracing::{Span, field, info, info_span, warn};
+
+/// Versions kept per dot before the oldest ones are pruned
+pub const DEFAULT_MAX_VERSIONS_PER_DOT: usize = 64;
+
+/// Entries returned when a request does not set a limit
+pub const DEFAULT_PAGE_SIZE: usize = 1000;
+
+/// Upper bound on entries returned in one page
+pub const MAX_PAGE_SIZE: usize = 10_000;
+
+/// Directory a runtime keeps dot state in, beside its documents
+pub const DOT_STATE_DIR: &str = "dot_state";
+
+/// Key of the list of dots that have state
+const DOT_INDEX_KEY: &[u8] = b"dot_state:index";
+
+type StateTrie = MerklePatriciaTrie<MptStorageAdapter>;
+
+#[derive(Error, Debug)]
+pub enum StateStoreError {
+    #[error("Dot not found: {0}")]
+    DotNotFound(String),
+    #[error("Dot state already exists: {0}")]
+    DotAlreadyExists(String),
+    #[error("Version {version} of dot {dot_id} has been pruned")]
+    VersionPruned { dot_id: String, version: u64 },
+    #[error("Version {version} not found for dot {dot_id}")]
+    VersionNotFound { dot_id: String, version: u64 },
+    #[error("State storage error: {0}")]
+    Storage(String),
+}
+
+/// Which entries of a dot's state to read
+#[derive(Debug, Clone, Default)]
+pub struct StateQuery {
+    /// Version to read, `None` for the current version
+    pub version: Option<u64>,
+    /// Only these keys, or every key when empty
+    pub keys: Vec<String>,
+    /// Only keys starting with this prefix
+    pub key_prefix: String,
+    /// Only keys sorting after this one
+    pub start_after: Option<String>,
+    /// Maximum entries to return, 0 for [`DEFAULT_PAGE_SIZE`]
+    pub limit: usize,
+}
+
+/// One page of a dot's state at a resolved version
+#[derive(Debug, Clone)]
+pub struct StatePage {
+    pub entries: BTreeMap<String, Vec<u8>>,
+    pub root_hash: [u8; 32],
+    pub version: u64,
+    /// Last key of this page when more entries match the query
+    pub next_key: Option<String>,
+}
+
+/// Which part of a diff between two versions to return
+#[derive(Debug, Clone, Default)]
+pub struct DiffQuery {
+    /// Only keys sorting after this one
+    pub start_after: Option<String>,
+    /// Maximum changes to return, 0 for [`DEFAULT_PAGE_SIZE`]
+    pub limit: usize,
+    /// Values larger than this are replaced by their hash, `None` to always inline
+    pub max_inline_value_size: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// A value in a diff, inlined or summarized when over the size threshold
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffValue {
+    Inline(Vec<u8>),
+    Hashed { sha256: [u8; 32], size: usize },
+}
+
+impl DiffValue {
+    fn new(bytes: Vec<u8>, max_inline: Option<usize>) -> Self {
+        match max_inline {
+            Some(max) if bytes.len() > max => DiffValue::Hashed {
+                sha256: Sha256::digest(&bytes).into(),
+                size: bytes.len(),
+            },
+            _ => DiffValue::Inline(bytes),
+        }
+    }
+}
+
+/// A key that changed between two versions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateChange {
+    pub key: String,
+    pub kind: ChangeKind,
+    pub old_value: Option<DiffValue>,
+    pub new_value: Option<DiffValue>,
+}
+
+/// One page of changes between two versions, in key order
+#[derive(Debug, Clone)]
+pub struct StateDiff {
+    pub from_version: u64,
+    pub to_version: u64,
+    pub from_root: [u8; 32],
+    pub to_root: [u8; 32],
+    pub changes: Vec<StateChange>,
+    /// Last key of this page when more changes follow
+    pub next_key: Option<String>,
+}
+
+struct DotState {
+    address: DotAddress,
+    trie: StateTrie,
+    /// Root of the current version, which the trie is kept at between commits
+    current_root: [u8; 32],
+    /// Every version ever committed for this dot, used to tell pruned versions from unknown ones
+    issued_versions: HashSet<u64>,
+}
+
+/// What is persisted of a dot besides its trie nodes
+#[derive(Debug, Serialize, Deserialize)]
+struct DotRecord {
+    issued_versions: Vec<u64>,
+    /// Versions retained by the version manager, oldest first
+    versions: Vec<VersionRecord>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VersionRecord {
+    version: u64,
+    timestamp: u64,
+    root: [u8; 32],
+    description: String,
+}
+
+/// Versioned state for all deployed dots
+pub struct DotStateStore {
+    db: Arc<dyn DatabaseInterface>,
+    dots: RwLock<HashMap<String, DotState>>,
+    versions: DotVersionManager,
+}
+
+impl DotStateStore {
+    /// A store kept in memory only
+    pub fn new(max_versions_per_dot: usize) -> Self {
+        let db = Database::new_in_memory().expect("an in-memory database opens");
+        Self {
+            db: Arc::new(db),
+            dots: RwLock::new(HashMap::new()),
+            versions: DotVersionManager::new(max_versions_per_dot),
+        }
+    }
+
+    /// A store persisted in `db`, with the state of the dots already stored there
+    pub fn open(db: Arc<dyn DatabaseInterface>, max_versions_per_dot: usize) -> Result<Self, StateStoreError> {
+        let store = Self {
+            db,
+            dots: RwLock::new(HashMap::new()),
+            versions: DotVersionManager::new(max_versions_per_dot),
+        };
+
+        let mut dots = store.dots.write().unwrap();
+        for dot_id in store.load_index()? {
+            let record: DotRecord = store
+                .load(&dot_record_key(&dot_id))?
+                .ok_or_else(|| StateStoreError::Storage(format!("Missing state record for dot {dot_id}")))?;
+            let address = dot_address(&dot_id);
+            for version in &record.versions {
+                let version = DotStateVersion::new(StateVersionId::new(version.version, version.timestamp), version.root, address, None, version.description.clone());
+                store.versions.load_version(version).map_err(|e| StateStoreError::Storage(e.to_string()))?;
+            }
+
+            let current_root = store
+                .versions
+                .get_current_version(address)
+                .ok_or_else(|| StateStoreError::Storage(format!("No current version for dot {dot_id}")))?
+                .mpt_root_hash;
+            let mut trie = store.new_trie();
+            trie.set_root(current_root);
+            dots.insert(
+                dot_id,
+                DotState {
+                    address,
+                    trie,
+                    current_root,
+                    issued_versions: record.issued_versions.into_iter().collect(),
+                },
+            );
+        }
+        info!("Opened state for {} dots", dots.len());
+        drop(dots);
+
+        Ok(store)
+    }
+
+    /// Create empty state for a newly deployed dot, returning its initial version
+    pub fn create_dot(&self, dot_id: &str) -> Result<u64, StateStoreError> {
+        let mut dots = self.dots.write().unwrap();
+        if dots.contains_key(dot_id) {
+            return Err(StateStoreError::DotAlreadyExists(dot_id.to_string()));
+        }
+
+        let address = dot_address(dot_id);
+        let trie = self.new_trie();
+        let root = trie.root_hash();
+        let version_id = self
+            .versions
+            .create_version(address, root, "Initial state".to_string())
+            .map_err(|e| StateStoreError::Storage(e.to_string()))?;
+
+        let state = DotState {
+            address,
+            trie,
+            current_root: root,
+            issued_versions: HashSet::from([version_id.logical_version()]),
+        };
+        self.persist(dot_id, &state)?;
+        dots.insert(dot_id.to_string(), state);
+        self.persist_index(&dots)?;
+
+        info!("Created state for dot {} at version {}", dot_id, version_id.logical_version());
+        Ok(version_id.logical_version())
+    }
+
+    /// Drop a dot's state
+    ///
+    /// Its trie nodes are left in the database, where other dots may share them.
+    pub fn remove_dot(&self, dot_id: &str) -> bool {
+        let mut dots = self.dots.write().unwrap();
+        if dots.remove(dot_id).is_none() {
+            return false;
+        }
+        if let Err(e) = self
+            .persist_index(&dots)
+            .and_then(|_| self.db.delete(&dot_record_key(dot_id)).map_err(|e| StateStoreError::Storage(e.to_string())))
+        {
+            warn!("Failed to remove the stored state record of dot {}: {}", dot_id, e);
+        }
+        true
+    }
+
+    /// Apply writes (`Some`) and deletions (`None`) as a new version, returning its number
+    pub fn commit(&self, dot_id: &str, changes: impl IntoIterator<Item = (String, Option<Vec<u8>>)>, description: String) -> Result<u64, StateStoreError> {
+        let span = dotdb_span("commit", dot_id);
+        let _entered = span.enter();
+        let mut dots = self.dots.write().unwrap();
+        let state = dots.get_mut(dot_id).ok_or_else(|| StateStoreError::DotNotFound(dot_id.to_string()))?;
+        let pages = PageReads::record(&span, &state.trie);
+
+        state.trie.set_root(state.current_root);
+        for (key, value) in changes {
+            let result = match value {
+                Some(value) => state.trie.put(key.into_bytes(), value),
+                None => state.trie.delete(&key.into_bytes()).map(|_| ()),
+            };
+            if let Err(e) = result {
+                // Leave the current version untouched on a partial write
+                state.trie.set_root(state.current_root);
+                return Err(StateStoreError::Storage(e.to_string()));
+            }
+        }
+        pages.finish(&state.trie);
+
+        let root = state.trie.root_hash();
+        let version_id = self.versions.create_version(state.address, root, description).map_err(|e| StateStoreError::Storage(e.to_string()))?;
+        state.current_root = root;
+        state.issued_versions.insert(version_id.logical_version());
+        self.persist(dot_id, state)?;
+
+        Ok(version_id.logical_version())
+    }
+
+    /// Read a page of a dot's state
+    ///
+    /// Historical versions are read at their root, so readers never wait on each other.
+    pub fn read(&self, dot_id: &str, query: &StateQuery) -> Result<StatePage, StateStoreError> {
+        let span = dotdb_span("read", dot_id);
+        let _entered = span.enter();
+        let dots = self.dots.read().unwrap();
+        let state = dots.get(dot_id).ok_or_else(|| StateStoreError::DotNotFound(dot_id.to_string()))?;
+        let pages = PageReads::record(&span, &state.trie);
+
+        let (version, root_hash) = self.resolve_version(dot_id, state, query.version)?;
+
+        let page = Self::read_page(&state.trie, root_hash, query);
+        pages.finish(&state.trie);
+
+        let (entries, next_key) = page?;
+        Ok(StatePage {
+            entries,
+            root_hash,
+            version,
+            next_key,
+        })
+    }
+
+    /// Changes between two versions of a dot's state, walking only the trie nodes that differ
+    pub fn diff_versions(&self, dot_id: &str, from: u64, to: u64, query: &DiffQuery) -> Result<StateDiff, StateStoreError> {
+        let span = dotdb_span("diff", dot_id);
+        let _entered = span.enter();
+        let dots = self.dots.read().unwrap();
+        let state = dots.get(dot_id).ok_or_else(|| StateStoreError::DotNotFound(dot_id.to_string()))?;
+        let pages = PageReads::record(&span, &state.trie);
+
+        let (from, from_root) = self.resolve_version(dot_id, state, Some(from))?;
+        let (to, to_root) = self.resolve_version(dot_id, state, Some(to))?;
+
+        let options = DiffOptions {
+            start_after: query.start_after.as_ref().map(|key| key.clone().into_bytes()),
+            limit: match query.limit {
+                0 => DEFAULT_PAGE_SIZE,
+                n => n.min(MAX_PAGE_SIZE),
+            },
+        };
+        let diff = state.trie.diff(from_root, to_root, &options).map_err(|e| StateStoreError::Storage(e.to_string()));
+        pages.finish(&state.trie);
+        let diff = diff?;
+
+        let value = |bytes: Vec<u8>| DiffValue::new(bytes, query.max_inline_value_size);
+        let changes: Vec<StateChange> = diff
+            .changes
+            .into_iter()
+            .map(|change| match change {
+                TrieChange::Added { key, value: new } => StateChange {
+                    key: String::from_utf8_lossy(&key).into_owned(),
+                    kind: ChangeKind::Added,
+                    old_value: None,
+                    new_value: Some(value(new)),
+                },
+                TrieChange::Removed { key, value: old } => StateChange {
+                    key: String::from_utf8_lossy(&key).into_owned(),
+                    kind: ChangeKind::Removed,
+                    old_value: Some(value(old)),
+                    new_value: None,
+                },
+                TrieChange::Modified { key, old, new } => StateChange {
+                    key: String::from_utf8_lossy(&key).into_owned(),
+                    kind: ChangeKind::Modified,
+                    old_value: Some(value(old)),
+                    new_value: Some(value(new)),
+                },
+            })
+            .collect();
+
+        let next_key = if diff.has_more { changes.last().map(|c| c.key.clone()) } else { None };
+        Ok(StateDiff {
+            from_version: from,
+            to_version: to,
+            from_root,
+            to_root,
+            changes,
+            next_key,
+        })
+    }
+
+    /// Make the state of an earlier version current again, as a new version
+    ///
+    /// Returns the current version unchanged when it already has that state.
+    pub fn restore_version(&self, dot_id: &str, version: u64) -> Result<u64, StateStoreError> {
+        let mut dots = self.dots.write().unwrap();
+        let state = dots.get_mut(dot_id).ok_or_else(|| StateStoreError::DotNotFound(dot_id.to_string()))?;
+
+        let (_, root) = self.resolve_version(dot_id, state, Some(version))?;
+        if root == state.current_root {
+            let (current, _) = self.resolve_version(dot_id, state, None)?;
+            return Ok(current);
+        }
+
+        let version_id = self
+            .versions
+            .create_version(state.address, root, format!("Restored version {version}"))
+            .map_err(|e| StateStoreError::Storage(e.to_string()))?;
+        state.trie.set_root(root);
+        state.current_root = root;
+        state.issued_versions.insert(version_id.logical_version());
+        self.persist(dot_id, state)?;
+
+        info!("Restored state of dot {} from version {} as version {}", dot_id, version, version_id.logical_version());
+        Ok(version_id.logical_version())
+    }
+
+    /// Current version of a dot's state
+    pub fn current_version(&self, dot_id: &str) -> Result<u64, StateStoreError> {
+        let span = dotdb_span("current_version", dot_id);
+        let _entered = span.enter();
+        let dots = self.dots.read().unwrap();
+        let state = dots.get(dot_id).ok_or_else(|| StateStoreError::DotNotFound(dot_id.to_string()))?;
+        let pages = PageReads::record(&span, &state.trie);
+
+        let (version, _) = self.resolve_version(dot_id, state, None)?;
+        pages.finish(&state.trie);
+        Ok(version)
+    }
+
+    /// Resolve a version number, or the current version for `None`, to its number and root hash
+    fn resolve_version(&self, dot_id: &str, state: &DotState, version: Option<u64>) -> Result<(u64, [u8; 32]), StateStoreError> {
+        let version = match version {
+            None => self.versions.get_current_version(state.address),
+            Some(number) => {
+                let found = self.versions.get_all_versions(state.address).into_iter().find(|v| v.version_id.logical_version() == number);
+                match found {
+                    Some(version) => Some(version),
+                    None if state.issued_versions.contains(&number) => {
+                        return Err(StateStoreError::VersionPruned {
+                            dot_id: dot_id.to_string(),
+                            version: number,
+                        });
+                    }
+                    None => {
+                        return Err(StateStoreError::VersionNotFound {
+                            dot_id: dot_id.to_string(),
+                            version: number,
+                        });
+                    }
+                }
+            }
+        };
+        let version = version.ok_or_else(|| StateStoreError::Storage(format!("No current version for dot {dot_id}")))?;
+
+        Ok((version.version_id.logical_version(), version.mpt_root_hash))
+    }
+
+    fn read_page(trie: &StateTrie, root: [u8; 32], query: &StateQuery) -> Result<(BTreeMap<String, Vec<u8>>, Option<String>), StateStoreError> {
+        let limit = match query.limit {
+            0 => DEFAULT_PAGE_SIZE,
+            n => n.min(MAX_PAGE_SIZE),
+        };
+
+        if query.keys.is_empty() {
+            let options = ScanOptions {
+                prefix: query.key_prefix.clone().into_bytes(),
+                start_after: query.start_after.clone().map(String::into_bytes),
+                limit,
+            };
+            let scan = trie.scan(root, &options).map_err(|e| StateStoreError::Storage(e.to_string()))?;
+            let entries: BTreeMap<String, Vec<u8>> = scan.entries.into_iter().filter_map(|(key, value)| Some((String::from_utf8(key).ok()?, value))).collect();
+            let next_key = if scan.has_more { entries.keys().next_back().cloned() } else { None };
+            return Ok((entries, next_key));
+        }
+
+        let mut candidates = query.keys.clone();
+        candidates.retain(|key| key.starts_with(&query.key_prefix) && query.start_after.as_ref().is_none_or(|after| key > after));
+        candidates.sort();
+        candidates.dedup();
+
+        let mut entries = BTreeMap::new();
+        let mut next_key = None;
+        for key in candidates {
+            let Some(value) = trie.get_at(root, &key.as_bytes().to_vec()).map_err(|e| StateStoreError::Storage(e.to_string()))? else {
+                continue;
+            };
+            if entries.len() == limit {
+                next_key = entries.keys().next_back().cloned();
+                break;
+            }
+            entries.insert(key, value);
+        }
+
+        Ok((entries, next_key))
+    }
+
+    fn new_trie(&self) -> StateTrie {
+        MerklePatriciaTrie::new(MptStorageAdapter::new(self.db.clone()))
+    }
+
+    /// Store the versions of a dot the version manager retains
+    fn persist(&self, dot_id: &str, state: &DotState) -> Result<(), StateStoreError> {
+        let mut issued_versions: Vec<u64> = state.issued_versions.iter().copied().collect();
+        issued_versions.sort_unstable();
+        let record = DotRecord {
+            issued_versions,
+            versions: self
+                .versions
+                .get_all_versions(state.address)
+                .into_iter()
+                .map(|version| VersionRecord {
+                    version: version.version_id.logical_version(),
+                    timestamp: version.version_id.timestamp(),
+                    root: version.mpt_root_hash,
+                    description: version.description,
+                })
+                .collect(),
+        };
+        self.store(dot_record_key(dot_id), &record)
+    }
+
+    fn persist_index(&self, dots: &HashMap<String, DotState>) -> Result<(), StateStoreError> {
+        let mut dot_ids: Vec<&String> = dots.keys().collect();
+        dot_ids.sort();
+        self.store(DOT_INDEX_KEY.to_vec(), &dot_ids)
+    }
+
+    fn load_index(&self) -> Result<Vec<String>, StateStoreError> {
+        Ok(self.load(DOT_INDEX_KEY)?.unwrap_or_default())
+    }
+
+    fn store(&self, key: Vec<u8>, value: &impl Serialize) -> Result<(), StateStoreError> {
+        let bytes = serde_json::to_vec(value).map_err(|e| StateStoreError::Storage(e.to_string()))?;
+        self.db.put(key, bytes).map_err(|e| StateStoreError::Storage(e.to_string()))
+    }
+
+    fn load<T: DeserializeOwned>(&self, key: &[u8]) -> Result<Option<T>, StateStoreError> {
+        match self.db.get(key).map_err(|e| StateStoreError::Storage(e.to_string()))? {
+            Some(bytes) => serde_json::from_slice(&bytes).map(Some).map_err(|e| StateStoreError::Storage(e.to_string())),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Default for DotStateStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_VERSIONS_PER_DOT)
+    }
+}
+
+/// Span for a storage operation performed on behalf of a dot; `pages_read` is filled in by [`PageReads`]
+fn dotdb_span(op: &'static str, dot_id: &str) -> Span {
+    info_span!("dotdb", op, dot_id, pages_read = field::Empty)
+}
+
+/// Records on a span how many trie nodes were read since it was created
+struct PageReads<'a> {
+    span: &'a Span,
+    start: u64,
+}
+
+impl<'a> PageReads<'a> {
+    fn record(span: &'a Span, trie: &StateTrie) -> Self {
+        Self { span, start: trie.node_reads() }
+    }
+
+    fn finish(self, trie: &StateTrie) {
+        self.span.record("pages_read", trie.node_reads().saturating_sub(self.start));
+    }
+}
+
+fn dot_record_key(dot_id: &str) -> Vec<u8> {
+    format!("dot_state:dot:{dot_id}").into_bytes()
+}
+
+/// Derive the storage address of a dot from its ID
+fn dot_address(dot_id: &str) -> DotAddress {
+    let digest = Sha256::digest(dot_id.as_bytes());
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&digest[..20]);
+    address
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(store: &DotStateStore, dot_id: &str, pairs: &[(&str, &str)]) -> u64 {
+        let changes = pairs.iter().map(|(k, v)| (k.to_string(), Some(v.as_bytes().to_vec())));
+        store.commit(dot_id, changes, "test".to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_reads_current_and_historical_versions() {
+        let store = DotStateStore::default();
+        store.create_dot("dot").unwrap();
+
+        let v1 = write(&store, "dot", &[("a", "1")]);
+        let v2 = write(&store, "dot", &[("a", "2"), ("b", "3")]);
+
+        let current = store.read("dot", &StateQuery::default()).unwrap();
+        assert_eq!(current.version, v2);
+        assert_eq!(current.entries.get("a").unwrap(), b"2");
+        assert_eq!(current.entries.len(), 2);
+
+        let old = store.read("dot", &StateQuery { version: Some(v1), ..Default::default() }).unwrap();
+        assert_eq!(old.version, v1);
+        assert_eq!(old.entries.get("a").unwrap(), b"1");
+        assert!(!old.entries.contains_key("b"));
+        assert_ne!(old.root_hash, current.root_hash);
+
+        // Reading history must not disturb the current version
+        write(&store, "dot", &[("c", "4")]);
+        assert_eq!(store.read("dot", &StateQuery::default()).unwrap().entries.len(), 3);
+    }
+
+    #[test]
+    fn test_prefix_filter_and_pagination() {
+        let store = DotStateStore::default();
+        store.create_dot("dot").unwrap();
+        write(&store, "dot", &[("user:1", "a"), ("user:2", "b"), ("user:3", "c"), ("config", "d")]);
+
+        let query = StateQuery {
+            key_prefix: "user:".to_string(),
+            limit: 2,
+            ..Default::default()
+        };
+        let first = store.read("dot", &query).unwrap();
+        assert_eq!(first.entries.keys().collect::<Vec<_>>(), vec!["user:1", "user:2"]);
+        assert_eq!(first.next_key.as_deref(), Some("user:2"));
+
+        let second = store
+            .read(
+                "dot",
+                &StateQuery {
+                    start_after: first.next_key,
+                    ..query
+                },
+            )
+            .unwrap();
+        assert_eq!(second.entries.keys().collect::<Vec<_>>(), vec!["user:3"]);
+        assert!(second.next_key.is_none());
+    }
+
+    #[test]
+    fn test_explicit_keys_skip_missing() {
+        let store = DotStateStore::default();
+        store.create_dot("dot").unwrap();
+        write(&store, "dot", &[("a", "1")]);
+
+        let page = store
+            .read(
+                "dot",
+                &StateQuery {
+                    keys: vec!["a".to_string(), "missing".to_string()],
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(page.entries.len(), 1);
+    }
+
+    #[test]
+    fn test_distinguishes_unknown_dot_pruned_and_unknown_versions() {
+        let store = DotStateStore::new(2);
+        let initial = store.create_dot("dot").unwrap();
+        write(&store, "dot", &[("a", "1")]);
+        write(&store, "dot", &[("a", "2")]);
+
+        assert!(matches!(store.read("other", &StateQuery::default()), Err(StateStoreError::DotNotFound(_))));
+        assert!(matches!(
+            store.read("dot", &StateQuery { version: Some(initial), ..Default::default() }),
+            Err(StateStoreError::VersionPruned { .. })
+        ));
+        assert!(matches!(
+            store.read("dot", &StateQuery { version: Some(u64::MAX), ..Default::default() }),
+            Err(StateStoreError::VersionNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_diff_between_versions() {
+        let store = DotStateStore::default();
+        store.create_dot("dot").unwrap();
+        let v1 = write(&store, "dot", &[("a", "1"), ("b", "2"), ("c", "3")]);
+        write(&store, "dot", &[("a", "10")]);
+        store.commit("dot", [("b".to_string(), None)], "delete".to_string()).unwrap();
+        let v4 = write(&store, "dot", &[("d", "4")]);
+
+        let diff = store.diff_versions("dot", v1, v4, &DiffQuery::default()).unwrap();
+        let summary: Vec<_> = diff.changes.iter().map(|c| (c.key.as_str(), c.kind)).collect();
+        assert_eq!(summary, vec![("a", ChangeKind::Modified), ("b", ChangeKind::Removed), ("d", ChangeKind::Added)]);
+        assert_eq!(diff.changes[0].old_value, Some(DiffValue::Inline(b"1".to_vec())));
+        assert_eq!(diff.changes[0].new_value, Some(DiffValue::Inline(b"10".to_vec())));
+        assert!(diff.next_key.is_none());
+
+        // Reversing the range swaps additions and removals
+        let reversed = store.diff_versions("dot", v4, v1, &DiffQuery::default()).unwrap();
+        assert_eq!(reversed.changes[1].kind, ChangeKind::Added);
+    }
+
+    #[test]
+    fn test_diff_pagination_and_hashed_values() {
+        let store = DotStateStore::default();
+        let v0 = store.create_dot("dot").unwrap();
+        let v1 = write(&store, "dot", &[("k1", "small"), ("k2", "a much larger value"), ("k3", "x")]);
+
+        let query = DiffQuery {
+            limit: 2,
+            max_inline_value_size: Some(8),
+            ..Default::default()
+        };
+        let first = store.diff_versions("dot", v0, v1, &query).unwrap();
+        assert_eq!(first.changes.len(), 2);
+        assert_eq!(first.next_key.as_deref(), Some("k2"));
+        assert!(matches!(first.changes[1].new_value, Some(DiffValue::Hashed { size: 19, .. })));
+
+        let second = store.diff_versions("dot", v0, v1, &DiffQuery { start_after: first.next_key, ..query }).unwrap();
+        assert_eq!(second.changes.len(), 1);
+        assert_eq!(second.changes[0].key, "k3");
+        assert!(second.next_key.is_none());
+    }
+
+    #[test]
+    fn test_delete_removes_key_in_new_version() {
+        let store = DotStateStore::default();
+        store.create_dot("dot").unwrap();
+        let v1 = write(&store, "dot", &[("a", "1")]);
+        store.commit("dot", [("a".to_string(), None)], "delete".to_string()).unwrap();
+
+        assert!(store.read("dot", &StateQuery::default()).unwrap().entries.is_empty());
+        assert_eq!(store.read("dot", &StateQuery { version: Some(v1), ..Default::default() }).unwrap().entries.len(), 1);
+    }
+
+    #[test]
+    fn test_reopened_store_keeps_versions() {
+        let db: Arc<dyn DatabaseInterface> = Arc::new(Database::new_in_memory().unwrap());
+        let store = DotStateStore::open(db.clone(), 2).unwrap();
+        let initial = store.create_dot("dot").unwrap();
+        let v1 = write(&store, "dot", &[("a", "1")]);
+        let v2 = write(&store, "dot", &[("a", "2"), ("b", "3")]);
+        store.create_dot("removed").unwrap();
+        assert!(store.remove_dot("removed"));
+        drop(store);
+
+        let reopened = DotStateStore::open(db, 2).unwrap();
+        assert_eq!(reopened.current_version("dot").unwrap(), v2);
+        assert_eq!(reopened.read("dot", &StateQuery { version: Some(v1), ..Default::default() }).unwrap().entries.get("a").unwrap(), b"1");
+        assert!(matches!(
+            reopened.read("dot", &StateQuery { version: Some(initial), ..Default::default() }),
+            Err(StateStoreError::VersionPruned { .. })
+        ));
+        assert!(matches!(reopened.current_version("removed"), Err(StateStoreError::DotNotFound(_))));
+
+        // New versions are numbered after the ones stored before
+        assert!(write(&reopened, "dot", &[("c", "4")]) > v2);
+        assert_eq!(reopened.read("dot", &StateQuery::default()).unwrap().entries.len(), 3);
+    }
+
+    #[test]
+    fn test_restore_version_makes_old_state_current() {
+        let store = DotStateStore::default();
+        store.create_dot("dot").unwrap();
+        let v1 = write(&store, "dot", &[("a", "1")]);
+        let v2 = write(&store, "dot", &[("a", "2"), ("b", "3")]);
+
+        let restored = store.restore_version("dot", v1).unwrap();
+        assert!(restored > v2);
+        let current = store.read("dot", &StateQuery::default()).unwrap();
+        assert_eq!(current.entries.get("a").unwrap(), b"1");
+        assert!(!current.entries.contains_key("b"));
+
+        // Restoring the state that is already current adds no version
+        assert_eq!(store.restore_version("dot", v1).unwrap(), restored);
+        // and the restored-over version stays readable
+        let replaced = StateQuery {
+            version: Some(v2),
+            ..Default::default()
+        };
+        assert_eq!(store.read("dot", &replaced).unwrap().entries.len(), 2);
+    }
+}
@@ -0,0 +1,136 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Reassembly of dot bytecode streamed in chunks
+
+use base64::{Engine as _, engine::general_purpose};
+use futures::{Stream, StreamExt};
+use sha2::{Digest, Sha256};
+use tonic::Status;
+
+use crate::proto::vm_service::{DeployDotChunk, DeployDotRequest, deploy_dot_chunk::Payload};
+
+/// Largest bytecode a streamed deployment may carry unless configured otherwise
+pub const DEFAULT_MAX_ARTIFACT_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Collect a streamed deployment into a request whose `dot_source` holds the bytecode
+/// base64-encoded, the way the gateway sends it in a single message.
+///
+/// Reading stops as soon as the bytecode outgrows `max_bytes`. Nothing survives a stream
+/// that fails or ends before its trailer, or whose trailer does not match the chunks.
+pub async fn assemble<S>(mut chunks: S, max_bytes: u64) -> Result<DeployDotRequest, Status>
+where
+    S: Stream<Item = Result<DeployDotChunk, Status>> + Unpin,
+{
+    let mut request = match chunks.next().await.transpose()?.and_then(|chunk| chunk.payload) {
+        Some(Payload::Header(request)) => request,
+        Some(_) => return Err(Status::invalid_argument("a streamed deployment must start with its header")),
+        None => return Err(Status::invalid_argument("deployment stream is empty")),
+    };
+
+    let mut bytecode = Vec::new();
+    let mut checksum = Sha256::new();
+    while let Some(chunk) = chunks.next().await.transpose()? {
+        match chunk.payload {
+            Some(Payload::Data(data)) => {
+                let received = bytecode.len() as u64;
+                if data.offset != received {
+                    return Err(Status::invalid_argument(format!("chunk at offset {} does not follow the {} bytes received", data.offset, received)));
+                }
+                if received + data.data.len() as u64 > max_bytes {
+                    return Err(Status::resource_exhausted(format!("bytecode exceeds the {} byte limit", max_bytes)));
+                }
+                checksum.update(&data.data);
+                bytecode.extend_from_slice(&data.data);
+            }
+            Some(Payload::Trailer(trailer)) => {
+                if trailer.total_bytes != bytecode.len() as u64 {
+                    return Err(Status::data_loss(format!("trailer declares {} bytes but {} were received", trailer.total_bytes, bytecode.len())));
+                }
+                let digest = hex::encode(checksum.finalize());
+                if !digest.eq_ignore_ascii_case(&trailer.sha256) {
+                    return Err(Status::data_loss(format!("bytecode checksum {} does not match {}", digest, trailer.sha256)));
+                }
+                request.dot_source = general_purpose::STANDARD.encode(&bytecode);
+                return Ok(request);
+            }
+            Some(Payload::Header(_)) => return Err(Status::invalid_argument("a streamed deployment has a single header")),
+            None => return Err(Status::invalid_argument("deployment chunk is empty")),
+        }
+    }
+
+    Err(Status::cancelled("deployment stream ended before its trailer"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::vm_service::{BytecodeChunk, DeployDotTrailer};
+
+    fn header(name: &str) -> DeployDotChunk {
+        DeployDotChunk {
+            payload: Some(Payload::Header(DeployDotRequest {
+                dot_name: name.to_string(),
+                ..Default::default()
+            })),
+        }
+    }
+
+    fn data(offset: u64, bytes: &[u8]) -> DeployDotChunk {
+        DeployDotChunk {
+            payload: Some(Payload::Data(BytecodeChunk { offset, data: bytes.to_vec() })),
+        }
+    }
+
+    fn trailer(bytes: &[u8]) -> DeployDotChunk {
+        DeployDotChunk {
+            payload: Some(Payload::Trailer(DeployDotTrailer {
+                total_bytes: bytes.len() as u64,
+                sha256: hex::encode(Sha256::digest(bytes)),
+            })),
+        }
+    }
+
+    fn stream(chunks: Vec<DeployDotChunk>) -> impl Stream<Item = Result<DeployDotChunk, Status>> + Unpin {
+        futures::stream::iter(chunks.into_iter().map(Ok))
+    }
+
+    #[tokio::test]
+    async fn test_assembles_chunks_in_order() {
+        let chunks = vec![header("big"), data(0, b"dot "), data(4, b"big {}"), trailer(b"dot big {}")];
+
+        let request = assemble(stream(chunks), 1024).await.unwrap();
+
+        assert_eq!(request.dot_name, "big");
+        assert_eq!(general_purpose::STANDARD.decode(&request.dot_source).unwrap(), b"dot big {}");
+    }
+
+    #[tokio::test]
+    async fn test_rejects_oversized_and_incomplete_streams() {
+        // The limit is enforced before the trailer arrives
+        let oversized = vec![header("big"), data(0, &[0; 8]), data(8, &[0; 8])];
+        assert_eq!(assemble(stream(oversized), 12).await.unwrap_err().code(), tonic::Code::ResourceExhausted);
+
+        let interrupted = vec![header("big"), data(0, b"dot ")];
+        assert_eq!(assemble(stream(interrupted), 1024).await.unwrap_err().code(), tonic::Code::Cancelled);
+
+        let out_of_order = vec![header("big"), data(4, b"big {}")];
+        assert_eq!(assemble(stream(out_of_order), 1024).await.unwrap_err().code(), tonic::Code::InvalidArgument);
+
+        let corrupted = vec![header("big"), data(0, b"dot big {}"), trailer(b"dot bug {}")];
+        assert_eq!(assemble(stream(corrupted), 1024).await.unwrap_err().code(), tonic::Code::DataLoss);
+    }
+}
@@ -0,0 +1,227 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Logs service implementation
+
+use dotvm_common::log_capture::{LOG_SHIPPING_TARGET, LogEvents, without_capture};
+use regex::Regex;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tonic::{Request, Response, Result as TonicResult, Status};
+use tracing::{debug, instrument, warn};
+
+use crate::proto::vm_service::{IngestLogsRequest, IngestLogsResponse, QueryLogsRequest, QueryLogsResponse};
+
+use super::store::{LogQuery, LogStore, LogStoreError, event_record, parse_level};
+
+/// Captured events stored per append
+const SHIP_BATCH: usize = 256;
+
+/// Logs service answers log queries and stores the events shipped to it
+pub struct LogsService {
+    store: Arc<LogStore>,
+}
+
+impl LogsService {
+    /// Keeps logs in memory only
+    pub fn new() -> Self {
+        Self::with_store(Arc::new(LogStore::new("local")))
+    }
+
+    pub fn with_store(store: Arc<LogStore>) -> Self {
+        Self { store }
+    }
+
+    pub fn store(&self) -> Arc<LogStore> {
+        Arc::clone(&self.store)
+    }
+
+    #[instrument(skip(self, request))]
+    pub async fn query_logs(&self, request: Request<QueryLogsRequest>) -> TonicResult<Response<QueryLogsResponse>> {
+        let query = log_query(request.into_inner()).map_err(store_status)?;
+        let result = self.store.query(&query).map_err(store_status)?;
+        // Debug only, as followers poll this continuously and their queries would fill the store
+        debug!("Log query returned {} records after reading {} documents", result.records.len(), result.documents_read);
+
+        Ok(Response::new(QueryLogsResponse {
+            records: result.records,
+            last_sequence: result.last_sequence,
+            documents_read: result.documents_read,
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    pub async fn ingest_logs(&self, request: Request<IngestLogsRequest>) -> TonicResult<Response<IngestLogsResponse>> {
+        let records = request.into_inner().records;
+        // Ingested records must not be captured again when the store logs while writing them
+        let accepted = without_capture(|| self.store.append(records)).map_err(store_status)?;
+
+        Ok(Response::new(IngestLogsResponse { accepted: accepted as u32 }))
+    }
+
+    /// Store the events this runtime captures, in batches, on a thread of its own until the
+    /// capture layer is dropped
+    pub fn spawn_shipper(&self, mut events: LogEvents) -> std::io::Result<JoinHandle<()>> {
+        let store = self.store();
+        std::thread::Builder::new().name("log-shipper".to_string()).spawn(move || {
+            let mut reported_drops = 0;
+            while let Some(event) = events.blocking_recv() {
+                let mut batch = vec![event_record(event, store.node())];
+                while batch.len() < SHIP_BATCH {
+                    match events.try_recv() {
+                        Some(event) => batch.push(event_record(event, store.node())),
+                        None => break,
+                    }
+                }
+                let count = batch.len();
+                if let Err(e) = without_capture(|| store.append(batch)) {
+                    warn!(target: LOG_SHIPPING_TARGET, "Failed to store {} log records: {}", count, e);
+                }
+                let dropped = events.dropped();
+                if dropped > reported_drops {
+                    warn!(target: LOG_SHIPPING_TARGET, "Dropped {} log records while the store was behind", dropped - reported_drops);
+                    reported_drops = dropped;
+                }
+            }
+        })
+    }
+
+    /// Remove expired records every `interval`
+    pub fn spawn_retention(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let store = self.store();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+                match without_capture(|| store.enforce_retention(now)) {
+                    Ok(0) => {}
+                    Ok(removed) => debug!(target: LOG_SHIPPING_TARGET, "Removed {} expired log records", removed),
+                    Err(e) => warn!(target: LOG_SHIPPING_TARGET, "Failed to remove expired log records: {}", e),
+                }
+            }
+        })
+    }
+}
+
+impl Default for LogsService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn log_query(request: QueryLogsRequest) -> Result<LogQuery, LogStoreError> {
+    let text = |value: String| (!value.is_empty()).then_some(value);
+    Ok(LogQuery {
+        min_level: text(request.level).map(|level| parse_level(&level)).transpose()?,
+        grep: text(request.grep).map(|pattern| Regex::new(&pattern)).transpose()?,
+        dot_id: text(request.dot_id),
+        node: text(request.node),
+        target: text(request.target),
+        since_ns: (request.since_ns > 0).then_some(request.since_ns),
+        until_ns: (request.until_ns > 0).then_some(request.until_ns),
+        limit: request.limit as usize,
+        after_sequence: (request.after_sequence > 0).then_some(request.after_sequence),
+    })
+}
+
+fn store_status(error: LogStoreError) -> Status {
+    match error {
+        LogStoreError::InvalidLevel(_) | LogStoreError::InvalidPattern(_) => Status::invalid_argument(error.to_string()),
+        LogStoreError::Storage(_) | LogStoreError::Index(_) => Status::internal(error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::vm_service::LogRecord;
+
+    #[tokio::test]
+    async fn test_ingest_then_query() {
+        let service = LogsService::new();
+        let record = |level: &str, message: &str| LogRecord {
+            timestamp_ns: 1_000,
+            level: level.to_string(),
+            message: message.to_string(),
+            node: "gateway-1".to_string(),
+            service: "dotlanth-api".to_string(),
+            dot_id: "dot-1".to_string(),
+            ..Default::default()
+        };
+        let ingested = service
+            .ingest_logs(Request::new(IngestLogsRequest {
+                records: vec![record("info", "request served"), record("warn", "slow request")],
+            }))
+            .await
+            .unwrap();
+        assert_eq!(ingested.into_inner().accepted, 2);
+
+        let response = service
+            .query_logs(Request::new(QueryLogsRequest {
+                dot_id: "dot-1".to_string(),
+                level: "WARN".to_string(),
+                ..Default::default()
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.records.len(), 1);
+        assert_eq!((response.records[0].message.as_str(), response.records[0].node.as_str()), ("slow request", "gateway-1"));
+        assert_eq!(response.last_sequence, 2);
+
+        for request in [
+            QueryLogsRequest {
+                level: "loud".to_string(),
+                ..Default::default()
+            },
+            QueryLogsRequest {
+                grep: "(".to_string(),
+                ..Default::default()
+            },
+        ] {
+            let status = service.query_logs(Request::new(request)).await.unwrap_err();
+            assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        }
+    }
+
+    #[test]
+    fn test_shipper_stores_captured_events() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let service = LogsService::with_store(Arc::new(LogStore::new("node-a")));
+        let (layer, events) = dotvm_common::log_capture::log_capture("dotvm-runtime", 16);
+        let shipper = service.spawn_shipper(events).unwrap();
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            let span = tracing::info_span!("execute", dot_id = "dot-7");
+            let _entered = span.enter();
+            tracing::error!("execution failed");
+        });
+        // The layer was dropped with the subscriber, so the shipper drains and exits
+        shipper.join().unwrap();
+
+        let result = service
+            .store()
+            .query(&LogQuery {
+                dot_id: Some("dot-7".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(result.records.len(), 1);
+        assert_eq!((result.records[0].level.as_str(), result.records[0].node.as_str()), ("ERROR", "node-a"));
+    }
+}
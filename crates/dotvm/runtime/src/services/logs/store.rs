@@ -0,0 +1,593 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Log store - tracing events of the runtime and gateways in a dotdb collection
+//!
+//! Every record is a document in [`LOG_COLLECTION`]. B+ tree indexes over the timestamp, the
+//! order records were received in, and the level, dot, node and target fields are rebuilt
+//! when the store opens and kept current by [`LogStore::append`] and retention, so a query
+//! only reads the documents its time range and field filters leave.
+
+use dotdb_core::document::{CollectionManager, DocumentError, DocumentId, create_in_memory_collection_manager, timestamp_key};
+use dotdb_core::indices::{BPlusTree, CompositeKey, Index, IndexError, RangeQuery};
+use dotvm_common::log_capture::LogEvent;
+use regex::Regex;
+use serde_json::{Value, json};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::Level;
+
+use crate::proto::vm_service::LogRecord;
+
+/// Collection holding one document per log record, in the runtime's namespace
+pub const LOG_COLLECTION: &str = "logs";
+
+/// Records returned by a query that sets no limit
+pub const DEFAULT_QUERY_LIMIT: usize = 100;
+
+/// Most records a single query returns
+pub const MAX_QUERY_LIMIT: usize = 10_000;
+
+/// Fields indexed by their numeric value, in order
+const ORDERED_FIELDS: [&str; 2] = ["timestamp", "sequence"];
+
+/// Fields indexed by exact value
+const EXACT_FIELDS: [&str; 4] = ["level", "dot_id", "node", "target"];
+
+const INDEX_ORDER: usize = 64;
+
+/// Levels from the most to the least severe
+const LEVELS: [Level; 5] = [Level::ERROR, Level::WARN, Level::INFO, Level::DEBUG, Level::TRACE];
+
+#[derive(Error, Debug)]
+pub enum LogStoreError {
+    #[error("Log storage failed: {0}")]
+    Storage(#[from] DocumentError),
+    #[error("Log index failed: {0}")]
+    Index(#[from] IndexError),
+    #[error("Invalid log level: {0}")]
+    InvalidLevel(String),
+    #[error("Invalid grep pattern: {0}")]
+    InvalidPattern(#[from] regex::Error),
+}
+
+/// How long records are kept; the oldest go first once either limit is exceeded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogRetention {
+    pub max_age: Duration,
+    pub max_entries: usize,
+}
+
+impl Default for LogRetention {
+    fn default() -> Self {
+        Self {
+            max_age: Duration::from_secs(7 * 24 * 60 * 60),
+            max_entries: 1_000_000,
+        }
+    }
+}
+
+/// Filters of a query; unset filters match every record
+#[derive(Debug, Clone, Default)]
+pub struct LogQuery {
+    pub dot_id: Option<String>,
+    /// Records at least this severe match
+    pub min_level: Option<Level>,
+    pub node: Option<String>,
+    pub target: Option<String>,
+    /// Inclusive, in nanoseconds since the Unix epoch
+    pub since_ns: Option<u64>,
+    /// Exclusive, in nanoseconds since the Unix epoch
+    pub until_ns: Option<u64>,
+    /// Matched against the message
+    pub grep: Option<Regex>,
+    /// The newest matching records are kept, or the oldest after `after_sequence`
+    pub limit: usize,
+    /// Only records received after this sequence, for tailing the store
+    pub after_sequence: Option<u64>,
+}
+
+/// Matching records, oldest first
+#[derive(Debug, Clone, Default)]
+pub struct LogQueryResult {
+    pub records: Vec<LogRecord>,
+    /// Sequence to continue from to receive only newer records
+    pub last_sequence: u64,
+    /// Documents read after the indexes pruned the candidates
+    pub documents_read: u64,
+}
+
+/// Level name as stored, e.g. `WARN` for `warn` or `warning`
+pub fn parse_level(level: &str) -> Result<Level, LogStoreError> {
+    let level = level.trim();
+    let level = if level.eq_ignore_ascii_case("warning") { "warn" } else { level };
+    Level::from_str(level).map_err(|_| LogStoreError::InvalidLevel(level.to_string()))
+}
+
+/// A captured event as a record emitted on `node`
+pub fn event_record(event: LogEvent, node: &str) -> LogRecord {
+    LogRecord {
+        id: String::new(),
+        timestamp_ns: event.timestamp_ns,
+        level: event.level,
+        target: event.target,
+        message: event.message,
+        dot_id: event.dot_id.unwrap_or_default(),
+        node: node.to_string(),
+        service: event.service,
+        fields: event.fields.into_iter().collect(),
+        sequence: 0,
+    }
+}
+
+struct LogIndexes {
+    trees: HashMap<&'static str, BPlusTree<CompositeKey, String>>,
+    last_sequence: u64,
+}
+
+impl LogIndexes {
+    fn tree(&self, field: &str) -> &BPlusTree<CompositeKey, String> {
+        &self.trees[field]
+    }
+
+    fn insert(&mut self, id: &str, document: &Value) -> Result<(), IndexError> {
+        for (field, key) in index_entries(id, document) {
+            self.trees.get_mut(field).expect("every indexed field has a tree").insert(key, id.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, id: &str, document: &Value) -> Result<(), IndexError> {
+        for (field, key) in index_entries(id, document) {
+            self.trees.get_mut(field).expect("every indexed field has a tree").delete(&key)?;
+        }
+        Ok(())
+    }
+}
+
+/// Tracing events of the runtime and gateways, queryable by time, level, dot, node and target
+pub struct LogStore {
+    documents: CollectionManager,
+    /// Node recorded on the events this runtime captures itself
+    node: String,
+    retention: LogRetention,
+    /// Held for writing while records are appended or expired, so sequences stay in order
+    indexes: RwLock<LogIndexes>,
+}
+
+impl LogStore {
+    /// Keeps records in memory only; see [`Self::open`] to persist them
+    pub fn new(node: &str) -> Self {
+        let documents = create_in_memory_collection_manager().expect("in-memory document storage cannot fail to open");
+        Self::open(documents, node, LogRetention::default()).expect("an empty log collection indexes without errors")
+    }
+
+    /// Keeps records in `documents`, e.g. the runtime's namespace of its document store,
+    /// indexing the records already there
+    pub fn open(documents: CollectionManager, node: &str, retention: LogRetention) -> Result<Self, LogStoreError> {
+        if !documents.collection_exists(LOG_COLLECTION)? {
+            documents.create_collection(LOG_COLLECTION)?;
+        }
+        let mut trees = HashMap::new();
+        for field in ORDERED_FIELDS {
+            trees.insert(field, documents.build_time_index(LOG_COLLECTION, field, INDEX_ORDER, 0.9)?);
+        }
+        for field in EXACT_FIELDS {
+            trees.insert(field, documents.build_field_index(LOG_COLLECTION, field, INDEX_ORDER, 0.9)?);
+        }
+        let (start, end) = ordered_range(0, i64::MAX);
+        let last_sequence = trees["sequence"]
+            .range_iter(&start, &end)
+            .last()
+            .and_then(|(key, _)| key.fields().first().and_then(|bytes| ordered_value(bytes)))
+            .unwrap_or_default();
+
+        Ok(Self {
+            documents,
+            node: node.to_string(),
+            retention,
+            indexes: RwLock::new(LogIndexes { trees, last_sequence }),
+        })
+    }
+
+    pub fn node(&self) -> &str {
+        &self.node
+    }
+
+    pub fn retention(&self) -> LogRetention {
+        self.retention
+    }
+
+    /// Number of stored records
+    pub fn len(&self) -> usize {
+        self.indexes.read().unwrap_or_else(|e| e.into_inner()).tree("timestamp").len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Store records in the given order, assigning each the next sequence
+    ///
+    /// Records without a node are attributed to this store's node. Returns the number stored.
+    pub fn append(&self, records: Vec<LogRecord>) -> Result<usize, LogStoreError> {
+        let mut indexes = self.indexes.write().unwrap_or_else(|e| e.into_inner());
+        let count = records.len();
+        for mut record in records {
+            record.level = parse_level(&record.level)?.to_string();
+            if record.node.is_empty() {
+                record.node = self.node.clone();
+            }
+            record.sequence = indexes.last_sequence + 1;
+            let document = to_document(&record);
+            let id = self.documents.insert_value(LOG_COLLECTION, document.clone())?;
+            indexes.insert(&id.to_string(), &document)?;
+            indexes.last_sequence = record.sequence;
+        }
+        Ok(count)
+    }
+
+    /// Records matching every filter of `query`, oldest first
+    pub fn query(&self, query: &LogQuery) -> Result<LogQueryResult, LogStoreError> {
+        let limit = match query.limit {
+            0 => DEFAULT_QUERY_LIMIT,
+            limit => limit.min(MAX_QUERY_LIMIT),
+        };
+        let since = query.since_ns.map_or(i64::MIN, clamp_ns);
+        let until = query.until_ns.map_or(i64::MAX, clamp_ns);
+
+        let (candidates, high_water) = {
+            let indexes = self.indexes.read().unwrap_or_else(|e| e.into_inner());
+            let allowed = Self::allowed_ids(&indexes, query);
+            let (field, start, end) = match query.after_sequence {
+                Some(after) => {
+                    let (start, end) = ordered_range(clamp_ns(after).saturating_add(1), i64::MAX);
+                    ("sequence", start, end)
+                }
+                None => {
+                    let (start, end) = ordered_range(since, until.saturating_sub(1));
+                    ("timestamp", start, end)
+                }
+            };
+            let candidates: Vec<String> = indexes
+                .tree(field)
+                .range_iter(&start, &end)
+                .map(|(_, id)| id)
+                .filter(|id| allowed.as_ref().is_none_or(|allowed| allowed.contains(id)))
+                .collect();
+            (candidates, indexes.last_sequence)
+        };
+
+        // Tailing keeps the oldest new records so none are skipped, a search keeps the newest
+        let tailing = query.after_sequence.is_some();
+        let ordered: Box<dyn Iterator<Item = &String>> = if tailing { Box::new(candidates.iter()) } else { Box::new(candidates.iter().rev()) };
+        let mut result = LogQueryResult::default();
+        for id in ordered {
+            if result.records.len() == limit {
+                break;
+            }
+            let document_id = DocumentId::from_string(id).map_err(|_| DocumentError::InvalidDocumentId(id.clone()))?;
+            // Records expired since the candidates were collected are skipped
+            let Some(document) = self.documents.get_value(LOG_COLLECTION, &document_id)? else {
+                continue;
+            };
+            result.documents_read += 1;
+            let record = from_document(id, &document);
+            let timestamp = clamp_ns(record.timestamp_ns);
+            if timestamp < since || timestamp >= until || query.grep.as_ref().is_some_and(|grep| !grep.is_match(&record.message)) {
+                continue;
+            }
+            result.records.push(record);
+        }
+
+        result.last_sequence = match result.records.last() {
+            Some(last) if tailing && result.records.len() == limit => last.sequence,
+            _ => high_water,
+        };
+        if !tailing {
+            result.records.reverse();
+        }
+        Ok(result)
+    }
+
+    /// Ids left by the exact field filters of `query`, or `None` when it sets none
+    fn allowed_ids(indexes: &LogIndexes, query: &LogQuery) -> Option<HashSet<String>> {
+        let mut filters: Vec<(&str, Vec<String>)> = Vec::new();
+        if let Some(min_level) = query.min_level.filter(|level| *level != Level::TRACE) {
+            filters.push(("level", LEVELS.iter().filter(|level| **level <= min_level).map(Level::to_string).collect()));
+        }
+        for (field, value) in [("dot_id", &query.dot_id), ("node", &query.node), ("target", &query.target)] {
+            if let Some(value) = value {
+                filters.push((field, vec![value.clone()]));
+            }
+        }
+
+        let mut allowed: Option<HashSet<String>> = None;
+        for (field, values) in filters {
+            let ids: HashSet<String> = values
+                .iter()
+                .flat_map(|value| {
+                    let prefix = serde_json::to_vec(value).unwrap_or_default();
+                    let start = CompositeKey::new(vec![prefix.clone()]);
+                    let end = CompositeKey::new(vec![prefix, vec![u8::MAX]]);
+                    indexes.tree(field).range_iter(&start, &end).map(|(_, id)| id)
+                })
+                .filter(|id| allowed.as_ref().is_none_or(|allowed| allowed.contains(id)))
+                .collect();
+            allowed = Some(ids);
+        }
+        allowed
+    }
+
+    /// Remove records older than the retention's age and, past its entry limit, the oldest
+    /// remaining ones. Returns the number removed.
+    pub fn enforce_retention(&self, now_ns: u64) -> Result<usize, LogStoreError> {
+        let mut indexes = self.indexes.write().unwrap_or_else(|e| e.into_inner());
+        let cutoff = CompositeKey::new(vec![timestamp_key(clamp_ns(now_ns.saturating_sub(self.retention.max_age.as_nanos() as u64)))]);
+        let excess = indexes.tree("timestamp").len().saturating_sub(self.retention.max_entries);
+
+        let (start, end) = ordered_range(i64::MIN, i64::MAX);
+        let expired: Vec<(CompositeKey, String)> = indexes
+            .tree("timestamp")
+            .range_iter(&start, &end)
+            .enumerate()
+            .take_while(|(position, (key, _))| *position < excess || *key < cutoff)
+            .map(|(_, entry)| entry)
+            .collect();
+
+        for (key, id) in &expired {
+            let document_id = DocumentId::from_string(id).map_err(|_| DocumentError::InvalidDocumentId(id.clone()))?;
+            match self.documents.get_value(LOG_COLLECTION, &document_id)? {
+                Some(document) => {
+                    indexes.remove(id, &document)?;
+                    self.documents.delete(LOG_COLLECTION, &document_id)?;
+                }
+                None => indexes.trees.get_mut("timestamp").expect("the timestamp index exists").delete(key)?,
+            }
+        }
+        Ok(expired.len())
+    }
+}
+
+/// Keys spanning the values `from..=to` of a field indexed by number
+fn ordered_range(from: i64, to: i64) -> (CompositeKey, CompositeKey) {
+    (CompositeKey::new(vec![timestamp_key(from)]), CompositeKey::new(vec![timestamp_key(to), vec![u8::MAX]]))
+}
+
+/// Inverse of [`timestamp_key`] for non-negative values
+fn ordered_value(bytes: &[u8]) -> Option<u64> {
+    Some(u64::from_be_bytes(bytes.try_into().ok()?) ^ (1 << 63))
+}
+
+/// Nanosecond timestamps as stored, which is how the time index reads them
+fn clamp_ns(ns: u64) -> i64 {
+    i64::try_from(ns).unwrap_or(i64::MAX)
+}
+
+fn index_entries(id: &str, document: &Value) -> Vec<(&'static str, CompositeKey)> {
+    let id = id.as_bytes().to_vec();
+    let ordered = ORDERED_FIELDS
+        .into_iter()
+        .filter_map(|field| document.get(field)?.as_i64().map(|value| (field, CompositeKey::new(vec![timestamp_key(value), id.clone()]))));
+    let exact = EXACT_FIELDS.into_iter().filter_map(|field| {
+        document
+            .get(field)
+            .map(|value| (field, CompositeKey::new(vec![serde_json::to_vec(value).unwrap_or_default(), id.clone()])))
+    });
+    ordered.chain(exact).collect()
+}
+
+fn to_document(record: &LogRecord) -> Value {
+    let mut document = json!({
+        "timestamp": clamp_ns(record.timestamp_ns),
+        "sequence": record.sequence,
+        "level": record.level,
+        "target": record.target,
+        "message": record.message,
+        "node": record.node,
+        "service": record.service,
+        "fields": record.fields,
+    });
+    // Records of no dot have no dot_id, so the dot index leaves them out
+    if !record.dot_id.is_empty() {
+        document["dot_id"] = json!(record.dot_id);
+    }
+    document
+}
+
+fn from_document(id: &str, document: &Value) -> LogRecord {
+    let text = |field: &str| document[field].as_str().unwrap_or_default().to_string();
+    LogRecord {
+        id: id.to_string(),
+        timestamp_ns: document["timestamp"].as_u64().unwrap_or_default(),
+        level: text("level"),
+        target: text("target"),
+        message: text("message"),
+        dot_id: text("dot_id"),
+        node: text("node"),
+        service: text("service"),
+        fields: document["fields"]
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter_map(|(name, value)| value.as_str().map(|value| (name.clone(), value.to_string())))
+            .collect(),
+        sequence: document["sequence"].as_u64().unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dotdb_core::document::create_persistent_collection_manager;
+
+    const SECOND: u64 = 1_000_000_000;
+
+    fn record(timestamp_s: u64, level: &str, dot_id: &str, message: &str) -> LogRecord {
+        LogRecord {
+            timestamp_ns: timestamp_s * SECOND,
+            level: level.to_string(),
+            target: "dotvm::exec".to_string(),
+            message: message.to_string(),
+            dot_id: dot_id.to_string(),
+            service: "dotvm-runtime".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn messages(result: &LogQueryResult) -> Vec<&str> {
+        result.records.iter().map(|record| record.message.as_str()).collect()
+    }
+
+    #[test]
+    fn test_filters_prune_by_index() {
+        let store = LogStore::new("node-a");
+        let records = (0..100).map(|i| record(1_000 + i, if i % 10 == 0 { "error" } else { "INFO" }, if i % 2 == 0 { "dot-1" } else { "" }, &format!("step {i}")));
+        assert_eq!(store.append(records.collect()).unwrap(), 100);
+
+        // Ten seconds of records, of which only the even ones belong to dot-1
+        let result = store
+            .query(&LogQuery {
+                dot_id: Some("dot-1".to_string()),
+                since_ns: Some(1_020 * SECOND),
+                until_ns: Some(1_030 * SECOND),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(messages(&result), ["step 20", "step 22", "step 24", "step 26", "step 28"]);
+        assert_eq!(result.documents_read, 5);
+        assert_eq!(result.last_sequence, 100);
+        assert_eq!(result.records[0].node, "node-a");
+        assert_eq!((result.records[0].level.as_str(), result.records[1].level.as_str()), ("ERROR", "INFO"));
+
+        let errors = store
+            .query(&LogQuery {
+                min_level: Some(parse_level("warning").unwrap()),
+                grep: Some(Regex::new(r"step [1-5]0$").unwrap()),
+                limit: 3,
+                ..Default::default()
+            })
+            .unwrap();
+        // The newest matches are kept, returned oldest first
+        assert_eq!(messages(&errors), ["step 30", "step 40", "step 50"]);
+        assert!(errors.documents_read <= 10);
+        assert!(errors.records.iter().all(|record| record.level == "ERROR" && record.dot_id == "dot-1"));
+    }
+
+    #[test]
+    fn test_tailing_continues_after_last_sequence() {
+        let store = LogStore::new("node-a");
+        store.append(vec![record(10, "INFO", "dot-1", "first"), record(11, "INFO", "dot-2", "other")]).unwrap();
+        let initial = store.query(&LogQuery::default()).unwrap();
+        assert_eq!(initial.last_sequence, 2);
+
+        // A record shipped late keeps its original timestamp but is still seen when tailing
+        store
+            .append(vec![record(5, "WARN", "dot-1", "late"), record(12, "INFO", "dot-1", "second"), record(13, "INFO", "dot-1", "third")])
+            .unwrap();
+        let query = |after_sequence| LogQuery {
+            dot_id: Some("dot-1".to_string()),
+            after_sequence: Some(after_sequence),
+            limit: 2,
+            ..Default::default()
+        };
+        let next = store.query(&query(initial.last_sequence)).unwrap();
+        assert_eq!(messages(&next), ["late", "second"]);
+        assert_eq!(next.last_sequence, 4);
+        let rest = store.query(&query(next.last_sequence)).unwrap();
+        assert_eq!(messages(&rest), ["third"]);
+        assert_eq!(rest.last_sequence, 5);
+        assert!(store.query(&query(rest.last_sequence)).unwrap().records.is_empty());
+    }
+
+    #[test]
+    fn test_retention_by_age_and_count() {
+        let documents = create_in_memory_collection_manager().unwrap();
+        let retention = LogRetention {
+            max_age: Duration::from_secs(60),
+            max_entries: 3,
+        };
+        let store = LogStore::open(documents, "node-a", retention).unwrap();
+        store.append((0..6).map(|i| record(100 + i * 10, "INFO", "dot-1", &format!("at {}", 100 + i * 10))).collect()).unwrap();
+
+        // Records before 120s are too old, then one more goes to stay within three entries
+        assert_eq!(store.enforce_retention(180 * SECOND).unwrap(), 3);
+        assert_eq!(store.len(), 3);
+        let result = store
+            .query(&LogQuery {
+                dot_id: Some("dot-1".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(messages(&result), ["at 130", "at 140", "at 150"]);
+        assert_eq!(store.documents.get_all_values(LOG_COLLECTION).unwrap().len(), 3);
+        assert_eq!(store.enforce_retention(180 * SECOND).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_rejects_unknown_levels() {
+        let store = LogStore::new("node-a");
+        assert!(matches!(store.append(vec![record(1, "loud", "", "x")]), Err(LogStoreError::InvalidLevel(_))));
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_indexes_and_sequence_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let store = LogStore::open(create_persistent_collection_manager(dir.path(), None).unwrap(), "node-a", LogRetention::default()).unwrap();
+            store.append(vec![record(10, "ERROR", "dot-1", "crashed"), record(11, "INFO", "dot-2", "started")]).unwrap();
+            store.documents.flush().unwrap();
+        }
+
+        let store = LogStore::open(create_persistent_collection_manager(dir.path(), None).unwrap(), "node-b", LogRetention::default()).unwrap();
+        let errors = store
+            .query(&LogQuery {
+                min_level: Some(Level::ERROR),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(messages(&errors), ["crashed"]);
+        assert_eq!((errors.documents_read, errors.last_sequence), (1, 2));
+
+        store
+            .append(vec![event_record(
+                LogEvent {
+                    timestamp_ns: 12 * SECOND,
+                    level: "WARN".to_string(),
+                    target: "dotvm::exec".to_string(),
+                    message: "slow".to_string(),
+                    service: "dotvm-runtime".to_string(),
+                    dot_id: None,
+                    fields: [("elapsed_ms".to_string(), "900".to_string())].into(),
+                },
+                store.node(),
+            )])
+            .unwrap();
+        let newest = store
+            .query(&LogQuery {
+                after_sequence: Some(2),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(newest.records[0].sequence, 3);
+        assert_eq!(newest.records[0].node, "node-b");
+        assert_eq!(newest.records[0].fields["elapsed_ms"], "900");
+    }
+}
@@ -16,12 +16,17 @@
 
 //! Metrics collector - collects and aggregates VM metrics
 
+use dotvm_core::memory::AllocationTracker;
+use dotvm_core::vm::execution_controller::FairQueue;
 use std::collections::HashMap;
+use std::sync::Arc;
 use thiserror::Error;
 use tracing::{error, info, instrument};
 
 use crate::proto::vm_service::{GetVmMetricsRequest, GetVmMetricsResponse, MetricDataPoint, VmMetric};
 
+use super::latency::{DotLatency, LatencyHistograms};
+
 #[derive(Error, Debug)]
 pub enum MetricsError {
     #[error("Collection failed: {0}")]
@@ -30,14 +35,190 @@ pub enum MetricsError {
     InvalidMetricName(String),
 }
 
+/// Per-dot live memory metric names
+pub const DOT_MEMORY_LIVE_BYTES: &str = "dot_memory_live_bytes";
+pub const DOT_MEMORY_LIVE_ALLOCATIONS: &str = "dot_memory_live_allocations";
+/// One data point per allocation older than the tracker's leak age
+pub const MEMORY_LEAK_SUSPECT: &str = "memory_leak_suspect_bytes";
+/// Per-dot scheduler queue metric names
+pub const DOT_QUEUE_DEPTH: &str = "dot_queue_depth";
+pub const DOT_TASKS_IN_FLIGHT: &str = "dot_tasks_in_flight";
+pub const DOT_TASKS_DISPATCHED: &str = "dot_tasks_dispatched_total";
+/// Times a dot's next task was passed over by more tasks of other dots than its fair-share bound
+pub const DOT_TASKS_STARVED: &str = "dot_tasks_starved_total";
+/// Percentiles reported for each per-dot latency histogram
+pub const LATENCY_QUANTILES: [f64; 3] = [0.5, 0.9, 0.99];
+
 /// Metrics collector gathers system and VM metrics
 pub struct MetricsCollector {
     // TODO: Add actual metrics storage and collection
+    tracker: Arc<AllocationTracker>,
+    fair_queue: Arc<FairQueue>,
+    latencies: Arc<LatencyHistograms>,
 }
 
 impl MetricsCollector {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(tracker: Arc<AllocationTracker>, fair_queue: Arc<FairQueue>, latencies: Arc<LatencyHistograms>) -> Self {
+        Self { tracker, fair_queue, latencies }
+    }
+
+    fn wants(request: &GetVmMetricsRequest, name: &str) -> bool {
+        Self::wants_named(&request.metric_names, name)
+    }
+
+    fn wants_named(metric_names: &[String], name: &str) -> bool {
+        metric_names.is_empty() || metric_names.iter().any(|n| n == name)
+    }
+
+    fn latency_labels(latency: &DotLatency) -> HashMap<String, String> {
+        HashMap::from([("dot_id".to_string(), latency.dot_id.clone())])
+    }
+
+    /// Count and sum of a latency histogram, shared by its summary and bucket forms
+    fn latency_totals(latency: &DotLatency, timestamp: u64) -> [VmMetric; 2] {
+        let name = latency.latency.metric_name();
+        let histogram = &latency.histogram;
+        [(format!("{name}_count"), histogram.count() as f64), (format!("{name}_sum"), histogram.sum())].map(|(name, value)| VmMetric {
+            name,
+            r#type: "counter".to_string(),
+            data_points: vec![MetricDataPoint { timestamp, value }],
+            labels: Self::latency_labels(latency),
+        })
+    }
+
+    /// p50, p90 and p99 of every dot's latency histograms, as summaries labelled with their quantile
+    fn latency_percentile_metrics(&self, request: &GetVmMetricsRequest, timestamp: u64) -> Vec<VmMetric> {
+        let mut metrics = Vec::new();
+        for latency in self.latencies.snapshot() {
+            let name = latency.latency.metric_name();
+            if !Self::wants(request, name) {
+                continue;
+            }
+            for quantile in LATENCY_QUANTILES {
+                let Some(value) = latency.histogram.quantile(quantile) else {
+                    continue;
+                };
+                let mut labels = Self::latency_labels(&latency);
+                labels.insert("quantile".to_string(), quantile.to_string());
+                metrics.push(VmMetric {
+                    name: name.to_string(),
+                    r#type: "summary".to_string(),
+                    data_points: vec![MetricDataPoint { timestamp, value }],
+                    labels,
+                });
+            }
+            metrics.extend(Self::latency_totals(&latency, timestamp));
+        }
+        metrics
+    }
+
+    /// Raw cumulative bucket counts of every dot's latency histograms, Prometheus style:
+    /// one `<name>_bucket` series per `le` bound, ending with `+Inf`, plus the count and sum
+    pub fn latency_bucket_metrics(&self, metric_names: &[String], timestamp: u64) -> Vec<VmMetric> {
+        let mut metrics = Vec::new();
+        for latency in self.latencies.snapshot() {
+            let name = latency.latency.metric_name();
+            if !Self::wants_named(metric_names, name) {
+                continue;
+            }
+            for (bound, count) in latency.histogram.cumulative() {
+                let mut labels = Self::latency_labels(&latency);
+                let le = if bound.is_finite() { bound.to_string() } else { "+Inf".to_string() };
+                labels.insert("le".to_string(), le);
+                metrics.push(VmMetric {
+                    name: format!("{name}_bucket"),
+                    r#type: "histogram".to_string(),
+                    data_points: vec![MetricDataPoint { timestamp, value: count as f64 }],
+                    labels,
+                });
+            }
+            metrics.extend(Self::latency_totals(&latency, timestamp));
+        }
+        metrics
+    }
+
+    /// Per-dot allocation totals and leak suspects from the allocation tracker
+    fn memory_tracking_metrics(&self, request: &GetVmMetricsRequest, timestamp: u64) -> Vec<VmMetric> {
+        let mut metrics = Vec::new();
+        let config = self.tracker.config();
+        if !config.enabled {
+            return metrics;
+        }
+
+        for usage in self.tracker.usage() {
+            let labels = HashMap::from([("dot_id".to_string(), usage.dot_id.clone())]);
+            if Self::wants(request, DOT_MEMORY_LIVE_BYTES) {
+                metrics.push(VmMetric {
+                    name: DOT_MEMORY_LIVE_BYTES.to_string(),
+                    r#type: "gauge".to_string(),
+                    data_points: vec![MetricDataPoint {
+                        timestamp,
+                        value: usage.live_bytes as f64,
+                    }],
+                    labels: labels.clone(),
+                });
+            }
+            if Self::wants(request, DOT_MEMORY_LIVE_ALLOCATIONS) {
+                metrics.push(VmMetric {
+                    name: DOT_MEMORY_LIVE_ALLOCATIONS.to_string(),
+                    r#type: "gauge".to_string(),
+                    data_points: vec![MetricDataPoint {
+                        timestamp,
+                        value: usage.live_allocations as f64,
+                    }],
+                    labels,
+                });
+            }
+        }
+
+        if Self::wants(request, MEMORY_LEAK_SUSPECT) {
+            let report = self.tracker.leak_report();
+            for entry in report.entries {
+                let mut labels = HashMap::from([
+                    ("dot_id".to_string(), entry.dot_id),
+                    ("handle".to_string(), format!("{:#x}", entry.handle.address())),
+                    ("age_secs".to_string(), entry.age.as_secs().to_string()),
+                    ("leak_age_secs".to_string(), report.leak_age.as_secs().to_string()),
+                ]);
+                if let Some(site) = entry.site {
+                    labels.insert("site".to_string(), site);
+                }
+                metrics.push(VmMetric {
+                    name: MEMORY_LEAK_SUSPECT.to_string(),
+                    r#type: "gauge".to_string(),
+                    data_points: vec![MetricDataPoint { timestamp, value: entry.size as f64 }],
+                    labels,
+                });
+            }
+        }
+
+        metrics
+    }
+
+    /// Queue depth, in-flight tasks and starvation counts of every dot's scheduler queue
+    fn scheduler_queue_metrics(&self, request: &GetVmMetricsRequest, timestamp: u64) -> Vec<VmMetric> {
+        let mut metrics = Vec::new();
+        for status in self.fair_queue.statuses() {
+            // Tasks without an owning dot share one queue, reported without a dot_id label
+            let labels: HashMap<String, String> = status.dot_id.iter().map(|dot_id| ("dot_id".to_string(), dot_id.clone())).collect();
+            let series = [
+                (DOT_QUEUE_DEPTH, "gauge", status.queued as f64),
+                (DOT_TASKS_IN_FLIGHT, "gauge", status.in_flight as f64),
+                (DOT_TASKS_DISPATCHED, "counter", status.dispatched as f64),
+                (DOT_TASKS_STARVED, "counter", status.starved as f64),
+            ];
+            for (name, kind, value) in series {
+                if Self::wants(request, name) {
+                    metrics.push(VmMetric {
+                        name: name.to_string(),
+                        r#type: kind.to_string(),
+                        data_points: vec![MetricDataPoint { timestamp, value }],
+                        labels: labels.clone(),
+                    });
+                }
+            }
+        }
+        metrics
     }
 
     #[instrument(skip(self, request))]
@@ -79,6 +260,10 @@ impl MetricsCollector {
             },
         });
 
+        metrics.extend(self.memory_tracking_metrics(&request, chrono::Utc::now().timestamp() as u64));
+        metrics.extend(self.scheduler_queue_metrics(&request, chrono::Utc::now().timestamp() as u64));
+        metrics.extend(self.latency_percentile_metrics(&request, chrono::Utc::now().timestamp() as u64));
+
         Ok(GetVmMetricsResponse { metrics })
     }
 }
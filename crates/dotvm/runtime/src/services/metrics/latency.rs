@@ -0,0 +1,456 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Per-dot latency histograms
+//!
+//! Execution duration, queue wait and state access time are counted per dot into
+//! fixed buckets, from which percentiles are estimated and which are exported as
+//! cumulative bucket counts. Dots that record nothing for the idle TTL are dropped,
+//! so memory follows the set of recently active dots.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Time from a request reaching the runtime to its dot starting to run
+pub const DOT_QUEUE_WAIT: &str = "dot_queue_wait_ms";
+/// Time a dot spends running
+pub const DOT_EXECUTION_DURATION: &str = "dot_execution_duration_ms";
+/// Time spent pinning the state version an execution starts from
+pub const DOT_STATE_ACCESS: &str = "dot_state_access_ms";
+
+/// Upper bounds of the default buckets, in milliseconds
+pub const DEFAULT_BUCKETS_MS: &[f64] = &[
+    0.05, 0.1, 0.25, 0.5, 0.75, 1.0, 1.5, 2.0, 3.0, 5.0, 7.5, 10.0, 15.0, 20.0, 30.0, 50.0, 75.0, 100.0, 150.0, 200.0, 300.0, 500.0, 750.0, 1000.0, 1500.0, 2000.0, 3000.0, 5000.0, 7500.0, 10000.0,
+    30000.0, 60000.0,
+];
+
+/// How long a dot that records nothing keeps its histograms
+pub const DEFAULT_IDLE_TTL: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum HistogramError {
+    #[error("A histogram needs at least one bucket")]
+    NoBuckets,
+    #[error("Bucket bounds must be positive, finite and strictly increasing, got {0:?}")]
+    InvalidBounds(Vec<f64>),
+}
+
+/// Check that `bounds` can be used as bucket upper bounds
+pub fn validate_buckets(bounds: &[f64]) -> Result<(), HistogramError> {
+    if bounds.is_empty() {
+        return Err(HistogramError::NoBuckets);
+    }
+    let ordered = bounds.windows(2).all(|pair| pair[0] < pair[1]);
+    if !ordered || bounds.iter().any(|bound| !bound.is_finite() || *bound <= 0.0) {
+        return Err(HistogramError::InvalidBounds(bounds.to_vec()));
+    }
+    Ok(())
+}
+
+/// The latencies histograms are kept for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Latency {
+    QueueWait,
+    Execution,
+    StateAccess,
+}
+
+impl Latency {
+    pub const ALL: [Latency; 3] = [Latency::QueueWait, Latency::Execution, Latency::StateAccess];
+
+    pub fn metric_name(self) -> &'static str {
+        match self {
+            Latency::QueueWait => DOT_QUEUE_WAIT,
+            Latency::Execution => DOT_EXECUTION_DURATION,
+            Latency::StateAccess => DOT_STATE_ACCESS,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Bucket bounds and how long idle dots are kept
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatencyConfig {
+    /// Upper bounds of the buckets in milliseconds; larger values land in an overflow bucket
+    pub buckets_ms: Vec<f64>,
+    pub idle_ttl: Duration,
+}
+
+impl Default for LatencyConfig {
+    fn default() -> Self {
+        Self {
+            buckets_ms: DEFAULT_BUCKETS_MS.to_vec(),
+            idle_ttl: DEFAULT_IDLE_TTL,
+        }
+    }
+}
+
+/// Counts of values in fixed buckets, each holding the values up to its bound
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    bounds: Vec<f64>,
+    /// One count per bound, then the overflow bucket
+    counts: Vec<u64>,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Histogram {
+    /// An empty histogram over bounds accepted by [`validate_buckets`]
+    pub fn new(bounds: Vec<f64>) -> Self {
+        let counts = vec![0; bounds.len() + 1];
+        Self {
+            bounds,
+            counts,
+            sum: 0.0,
+            min: f64::INFINITY,
+            max: 0.0,
+        }
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        let value = value.max(0.0);
+        let bucket = self.bucket_of(value);
+        self.counts[bucket] += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    pub fn bounds(&self) -> &[f64] {
+        &self.bounds
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    /// Number of values up to each bound, ending with the total under an infinite bound
+    pub fn cumulative(&self) -> Vec<(f64, u64)> {
+        let bounds = self.bounds.iter().copied().chain([f64::INFINITY]);
+        bounds
+            .zip(self.counts.iter().scan(0, |total, count| {
+                *total += count;
+                Some(*total)
+            }))
+            .collect()
+    }
+
+    /// Estimate the `q` quantile, for `q` between 0 and 1
+    ///
+    /// Values are taken to be spread evenly within their bucket, which is narrowed to
+    /// the smallest and largest values seen, so the overflow bucket interpolates too.
+    /// An empty histogram has no quantiles.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = q.clamp(0.0, 1.0) * count as f64;
+        let mut below = 0;
+        for (index, &in_bucket) in self.counts.iter().enumerate() {
+            if in_bucket == 0 || ((below + in_bucket) as f64) < rank {
+                below += in_bucket;
+                continue;
+            }
+            let lower = if index == 0 { 0.0 } else { self.bounds[index - 1] }.max(self.min);
+            let upper = self.bounds.get(index).copied().unwrap_or(f64::INFINITY).min(self.max);
+            let fraction = (rank - below as f64) / in_bucket as f64;
+            return Some(lower + (upper - lower).max(0.0) * fraction);
+        }
+        Some(self.max)
+    }
+
+    /// Move the counts onto new bounds
+    ///
+    /// Each old bucket's values are counted as if they sat at its upper bound. The
+    /// total and sum are kept, and the cumulative count at every bound the old and new
+    /// bounds share is unchanged, so exported bucket series never go backwards.
+    fn rebucket(&mut self, bounds: &[f64]) {
+        let mut counts = vec![0; bounds.len() + 1];
+        for (index, &in_bucket) in self.counts.iter().enumerate() {
+            let upper = self.bounds.get(index).copied().unwrap_or(self.max).min(self.max);
+            counts[bounds.partition_point(|bound| *bound < upper)] += in_bucket;
+        }
+        self.bounds = bounds.to_vec();
+        self.counts = counts;
+    }
+
+    fn bucket_of(&self, value: f64) -> usize {
+        self.bounds.partition_point(|bound| *bound < value)
+    }
+}
+
+/// One dot's histogram of one latency
+#[derive(Debug, Clone, PartialEq)]
+pub struct DotLatency {
+    pub dot_id: String,
+    pub latency: Latency,
+    pub histogram: Histogram,
+}
+
+struct DotHistograms {
+    histograms: [Histogram; 3],
+    last_recorded: Instant,
+}
+
+struct Inner {
+    config: LatencyConfig,
+    dots: HashMap<String, DotHistograms>,
+}
+
+/// Latency histograms of every recently active dot
+pub struct LatencyHistograms {
+    inner: Mutex<Inner>,
+}
+
+impl Default for LatencyHistograms {
+    fn default() -> Self {
+        Self::new(LatencyConfig::default()).expect("the default buckets are valid")
+    }
+}
+
+impl LatencyHistograms {
+    pub fn new(config: LatencyConfig) -> Result<Self, HistogramError> {
+        validate_buckets(&config.buckets_ms)?;
+        Ok(Self {
+            inner: Mutex::new(Inner { config, dots: HashMap::new() }),
+        })
+    }
+
+    pub fn config(&self) -> LatencyConfig {
+        self.inner.lock().unwrap().config.clone()
+    }
+
+    /// Count `duration` into the dot's histogram of `latency`
+    pub fn record(&self, dot_id: &str, latency: Latency, duration: Duration) {
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.dots.contains_key(dot_id) {
+            // Only a new dot grows the map, so that is when idle ones are swept
+            Self::evict_idle(&mut inner, now);
+            let histograms = std::array::from_fn(|_| Histogram::new(inner.config.buckets_ms.clone()));
+            inner.dots.insert(dot_id.to_string(), DotHistograms { histograms, last_recorded: now });
+        }
+        let dot = inner.dots.get_mut(dot_id).expect("inserted above");
+        dot.histograms[latency.index()].observe(duration.as_secs_f64() * 1000.0);
+        dot.last_recorded = now;
+    }
+
+    /// Switch every histogram to new bucket bounds, keeping what was counted
+    pub fn set_buckets(&self, buckets_ms: Vec<f64>) -> Result<(), HistogramError> {
+        validate_buckets(&buckets_ms)?;
+        let mut inner = self.inner.lock().unwrap();
+        for dot in inner.dots.values_mut() {
+            for histogram in &mut dot.histograms {
+                histogram.rebucket(&buckets_ms);
+            }
+        }
+        inner.config.buckets_ms = buckets_ms;
+        Ok(())
+    }
+
+    pub fn set_idle_ttl(&self, idle_ttl: Duration) {
+        self.inner.lock().unwrap().config.idle_ttl = idle_ttl;
+    }
+
+    /// Histograms of every dot active within the idle TTL that recorded the latency at least once
+    pub fn snapshot(&self) -> Vec<DotLatency> {
+        self.snapshot_at(Instant::now())
+    }
+
+    /// Number of dots histograms are held for
+    pub fn tracked_dots(&self) -> usize {
+        self.inner.lock().unwrap().dots.len()
+    }
+
+    fn snapshot_at(&self, now: Instant) -> Vec<DotLatency> {
+        let mut inner = self.inner.lock().unwrap();
+        Self::evict_idle(&mut inner, now);
+        let mut snapshot: Vec<DotLatency> = inner
+            .dots
+            .iter()
+            .flat_map(|(dot_id, dot)| {
+                Latency::ALL.into_iter().filter(|latency| dot.histograms[latency.index()].count() > 0).map(|latency| DotLatency {
+                    dot_id: dot_id.clone(),
+                    latency,
+                    histogram: dot.histograms[latency.index()].clone(),
+                })
+            })
+            .collect();
+        snapshot.sort_by(|a, b| (&a.dot_id, a.latency.index()).cmp(&(&b.dot_id, b.latency.index())));
+        snapshot
+    }
+
+    fn evict_idle(inner: &mut Inner, now: Instant) {
+        let idle_ttl = inner.config.idle_ttl;
+        inner.dots.retain(|_, dot| now.saturating_duration_since(dot.last_recorded) < idle_ttl);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observed(bounds: &[f64], values: &[f64]) -> Histogram {
+        let mut histogram = Histogram::new(bounds.to_vec());
+        for value in values {
+            histogram.observe(*value);
+        }
+        histogram
+    }
+
+    /// The `q` quantile of sorted values, by the nearest-rank method
+    fn exact_quantile(sorted: &[f64], q: f64) -> f64 {
+        let rank = (q * sorted.len() as f64).ceil().max(1.0) as usize;
+        sorted[rank - 1]
+    }
+
+    #[test]
+    fn test_bucket_bounds_are_validated() {
+        assert_eq!(validate_buckets(&[]), Err(HistogramError::NoBuckets));
+        assert!(validate_buckets(&[1.0, 1.0]).is_err());
+        assert!(validate_buckets(&[2.0, 1.0]).is_err());
+        assert!(validate_buckets(&[0.0, 1.0]).is_err());
+        assert!(validate_buckets(&[1.0, f64::INFINITY]).is_err());
+        assert!(validate_buckets(DEFAULT_BUCKETS_MS).is_ok());
+        assert!(
+            LatencyHistograms::new(LatencyConfig {
+                buckets_ms: vec![],
+                idle_ttl: DEFAULT_IDLE_TTL
+            })
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_values_land_in_the_first_bucket_that_holds_them() {
+        let histogram = observed(&[1.0, 10.0], &[0.5, 1.0, 1.5, 10.0, 11.0]);
+        assert_eq!(histogram.cumulative(), vec![(1.0, 2), (10.0, 4), (f64::INFINITY, 5)]);
+        assert_eq!(histogram.count(), 5);
+        assert_eq!(histogram.sum(), 24.0);
+        assert_eq!(Histogram::new(vec![1.0]).quantile(0.5), None);
+    }
+
+    #[test]
+    fn test_quantiles_interpolate_within_buckets() {
+        let histogram = observed(&[10.0, 20.0], &[12.0, 14.0, 16.0, 18.0]);
+        // All four values share a bucket, narrowed to the 12..18 they span
+        assert_eq!(histogram.quantile(0.5), Some(15.0));
+        assert_eq!(histogram.quantile(1.0), Some(18.0));
+
+        // Values past the last bound still interpolate, up to the largest seen
+        let histogram = observed(&[1.0], &[0.5, 100.0, 200.0]);
+        assert_eq!(histogram.quantile(1.0), Some(200.0));
+        assert!(histogram.quantile(0.9).unwrap() > 100.0);
+    }
+
+    #[test]
+    fn test_percentiles_match_known_durations() {
+        // A recorded mix of fast calls, typical executions and a slow tail, in milliseconds
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut durations: Vec<f64> = (0..20_000)
+            .map(|i| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                let unit = (state >> 11) as f64 / (1u64 << 53) as f64;
+                match i % 20 {
+                    0 => 500.0 + unit * 4500.0,
+                    1..=4 => 0.1 + unit * 0.9,
+                    _ => (unit * 5.0).exp() * 2.0,
+                }
+            })
+            .collect();
+
+        let histograms = LatencyHistograms::default();
+        for duration in &durations {
+            histograms.record("burn-in", Latency::Execution, Duration::from_secs_f64(duration / 1000.0));
+        }
+        durations.sort_by(f64::total_cmp);
+
+        let snapshot = histograms.snapshot();
+        assert_eq!(snapshot.len(), 1);
+        let histogram = &snapshot[0].histogram;
+        assert_eq!(histogram.count(), durations.len() as u64);
+        for q in [0.5, 0.9, 0.99] {
+            let exact = exact_quantile(&durations, q);
+            let estimate = histogram.quantile(q).unwrap();
+            let error = (estimate - exact).abs() / exact;
+            assert!(error < 0.05, "p{} estimated {:.3}ms, exactly {:.3}ms ({:.1}% off)", q * 100.0, estimate, exact, error * 100.0);
+        }
+    }
+
+    #[test]
+    fn test_rebucketing_keeps_cumulative_counts() {
+        let histograms = LatencyHistograms::new(LatencyConfig {
+            buckets_ms: vec![1.0, 5.0, 10.0],
+            idle_ttl: DEFAULT_IDLE_TTL,
+        })
+        .unwrap();
+        for ms in [0.5, 2.0, 4.0, 7.0, 12.0] {
+            histograms.record("a", Latency::Execution, Duration::from_secs_f64(ms / 1000.0));
+        }
+        let before = histograms.snapshot()[0].histogram.clone();
+
+        histograms.set_buckets(vec![1.0, 2.5, 5.0, 20.0]).unwrap();
+        let after = histograms.snapshot()[0].histogram.clone();
+        assert_eq!(after.bounds(), &[1.0, 2.5, 5.0, 20.0]);
+        assert_eq!(after.count(), before.count());
+        assert_eq!(after.sum(), before.sum());
+        let cumulative = after.cumulative();
+        assert!(cumulative.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+        // Shared bounds keep their counts
+        assert_eq!(cumulative[0], (1.0, 1));
+        assert_eq!(cumulative[2], (5.0, 3));
+        assert_eq!(cumulative[4], (f64::INFINITY, 5));
+
+        // New values use the new buckets
+        histograms.record("a", Latency::Execution, Duration::from_millis(15));
+        assert_eq!(histograms.snapshot()[0].histogram.cumulative()[3], (20.0, 6));
+        assert!(histograms.set_buckets(vec![5.0, 1.0]).is_err());
+        assert_eq!(histograms.config().buckets_ms, vec![1.0, 2.5, 5.0, 20.0]);
+    }
+
+    #[test]
+    fn test_idle_dots_are_evicted() {
+        let histograms = LatencyHistograms::default();
+        histograms.set_idle_ttl(Duration::from_secs(60));
+        histograms.record("idle", Latency::QueueWait, Duration::from_millis(1));
+        histograms.record("busy", Latency::StateAccess, Duration::from_millis(1));
+        assert_eq!(histograms.tracked_dots(), 2);
+
+        let later = Instant::now() + Duration::from_secs(61);
+        assert!(histograms.snapshot_at(later).is_empty());
+        assert_eq!(histograms.tracked_dots(), 0);
+
+        // A dot only reports the latencies it recorded
+        histograms.record("busy", Latency::StateAccess, Duration::from_millis(1));
+        let snapshot = histograms.snapshot();
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!((snapshot[0].dot_id.as_str(), snapshot[0].latency), ("busy", Latency::StateAccess));
+    }
+}
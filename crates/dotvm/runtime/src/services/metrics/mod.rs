@@ -17,6 +17,7 @@
 //! Metrics service - handles VM metrics, monitoring, and observability
 
 pub mod collector;
+pub mod latency;
 pub mod service;
 
 pub use service::MetricsService;
@@ -16,26 +16,70 @@
 
 //! Metrics service implementation
 
+use dotvm_core::memory::{AllocationTracker, TrackingConfig};
+use dotvm_core::vm::execution_controller::FairQueue;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response, Result as TonicResult, Status};
-use tracing::{error, info, instrument};
+use tracing::{debug, error, info, instrument};
 
-use crate::proto::vm_service::{GetVmMetricsRequest, GetVmMetricsResponse, MetricDataPoint, VmMetric};
+use crate::proto::vm_service::{
+    GetVmMetricsRequest, GetVmMetricsResponse, MemoryTrackingConfig, MetricDataPoint, SetMemoryTrackingRequest, SetMemoryTrackingResponse, StreamVmMetricsRequest, VmMetric,
+};
 
 use super::collector::MetricsCollector;
+use super::latency::LatencyHistograms;
 
 /// Metrics service handles all metrics-related operations
 pub struct MetricsService {
     collector: Arc<MetricsCollector>,
+    tracker: Arc<AllocationTracker>,
+    fair_queue: Arc<FairQueue>,
+    latencies: Arc<LatencyHistograms>,
 }
 
 impl MetricsService {
     pub fn new() -> Self {
+        Self::with_allocation_tracker(Arc::new(AllocationTracker::default()))
+    }
+
+    /// Reports per-dot memory from a tracker shared with the dots' memory managers
+    pub fn with_allocation_tracker(tracker: Arc<AllocationTracker>) -> Self {
+        Self::with_shared(tracker, Arc::new(FairQueue::default()))
+    }
+
+    /// Also reports per-dot scheduler queues from the fair queue of the dots' execution controller
+    pub fn with_shared(tracker: Arc<AllocationTracker>, fair_queue: Arc<FairQueue>) -> Self {
+        let latencies = Arc::new(LatencyHistograms::default());
         Self {
-            collector: Arc::new(MetricsCollector::new()),
+            collector: Arc::new(MetricsCollector::new(Arc::clone(&tracker), Arc::clone(&fair_queue), Arc::clone(&latencies))),
+            tracker,
+            fair_queue,
+            latencies,
         }
     }
 
+    /// Reports the per-dot latency histograms the dots service records into `latencies`
+    pub fn with_latencies(mut self, latencies: Arc<LatencyHistograms>) -> Self {
+        self.collector = Arc::new(MetricsCollector::new(Arc::clone(&self.tracker), Arc::clone(&self.fair_queue), Arc::clone(&latencies)));
+        self.latencies = latencies;
+        self
+    }
+
+    pub fn allocation_tracker(&self) -> &Arc<AllocationTracker> {
+        &self.tracker
+    }
+
+    pub fn fair_queue(&self) -> &Arc<FairQueue> {
+        &self.fair_queue
+    }
+
+    pub fn latencies(&self) -> &Arc<LatencyHistograms> {
+        &self.latencies
+    }
+
     #[instrument(skip(self, request))]
     pub async fn get_vm_metrics(&self, request: Request<GetVmMetricsRequest>) -> TonicResult<Response<GetVmMetricsResponse>> {
         let req = request.into_inner();
@@ -46,4 +90,64 @@ impl MetricsService {
 
         Ok(Response::new(result))
     }
+
+    /// Stream the raw bucket counts of the per-dot latency histograms every `interval_seconds`
+    ///
+    /// The stream ends when the client goes away.
+    #[instrument(skip(self, request))]
+    pub async fn stream_vm_metrics(&self, request: Request<StreamVmMetricsRequest>) -> TonicResult<Response<ReceiverStream<Result<VmMetric, Status>>>> {
+        let req = request.into_inner();
+        let interval = Duration::from_secs(u64::from(req.interval_seconds.max(1)));
+        info!("Streaming VM metrics every {:?}", interval);
+
+        let collector = Arc::clone(&self.collector);
+        let (tx, rx) = mpsc::channel(256);
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            loop {
+                ticks.tick().await;
+                for metric in collector.latency_bucket_metrics(&req.metric_names, chrono::Utc::now().timestamp() as u64) {
+                    if tx.send(Ok(metric)).await.is_err() {
+                        debug!("VM metrics stream closed");
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    #[instrument(skip(self, request))]
+    pub async fn set_memory_tracking(&self, request: Request<SetMemoryTrackingRequest>) -> TonicResult<Response<SetMemoryTrackingResponse>> {
+        let req = request.into_inner();
+
+        // Without a config the current settings are returned unchanged
+        if let Some(config) = req.config {
+            if config.enabled && config.leak_age_secs == 0 {
+                return Err(Status::invalid_argument("leak_age_secs must be greater than zero"));
+            }
+
+            self.tracker.configure(TrackingConfig {
+                enabled: config.enabled,
+                capture_backtraces: config.capture_backtraces,
+                leak_age: Duration::from_secs(config.leak_age_secs),
+            });
+            info!(
+                "Memory tracking updated: enabled={}, backtraces={}, leak_age={}s",
+                config.enabled, config.capture_backtraces, config.leak_age_secs
+            );
+        }
+
+        let applied = self.tracker.config();
+        Ok(Response::new(SetMemoryTrackingResponse {
+            success: true,
+            config: Some(MemoryTrackingConfig {
+                enabled: applied.enabled,
+                capture_backtraces: applied.capture_backtraces,
+                leak_age_secs: applied.leak_age.as_secs(),
+            }),
+            error_message: String::new(),
+        }))
+    }
 }
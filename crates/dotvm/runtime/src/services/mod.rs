@@ -21,7 +21,9 @@ pub mod abi;
 pub mod cluster;
 pub mod database;
 pub mod dots;
+pub mod logs;
 pub mod metrics;
+pub mod replication;
 pub mod vm_management;
 
 // Unified VM service that coordinates all sub-services
@@ -35,6 +37,7 @@ pub use abi::AbiService;
 pub use cluster::ClusterServiceImpl;
 pub use database::DatabaseServiceImpl;
 pub use dots::DotsService;
+pub use logs::LogsService;
 pub use metrics::MetricsService;
 pub use vm_management::VmManagementService;
 pub use vm_service::VmServiceImpl;
@@ -0,0 +1,519 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Document replication between runtime nodes
+//!
+//! A primary serves its change feed through `ReplicateChanges` and consistent
+//! snapshots through `GetSnapshot`. A replica runs a [`ReplicaFollower`], which
+//! bootstraps from a snapshot, streams the changes after it and reports its
+//! applied sequence after every batch. When the stream breaks, the follower
+//! reconnects with backoff and resumes after the last applied sequence; when the
+//! primary no longer holds those changes it bootstraps from a new snapshot.
+
+use dotdb_core::document::{ChangeFeedStorage, ChangeRecord, CollectionManager, DocumentError, Replica};
+use futures::{Stream, StreamExt};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::{Request, Status};
+use tracing::{info, warn};
+
+use crate::proto::database_service::database_service_client::DatabaseServiceClient;
+use crate::proto::database_service::{ChangeBatch, GetReplicationStatusResponse, GetSnapshotRequest, ReplicaProgress, ReplicaStatus, SnapshotChunk};
+
+/// Most change records sent in one batch
+const MAX_BATCH_RECORDS: usize = 512;
+/// Size of the snapshot chunks `GetSnapshot` streams
+const SNAPSHOT_CHUNK_BYTES: usize = 1024 * 1024;
+/// How long a change stream waits for new records before checking the replica is still there
+const FEED_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const MIN_RECONNECT_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(5);
+/// Response metadata naming the primary when a replica refuses a write
+pub const PRIMARY_METADATA_KEY: &str = "x-dotdb-primary";
+
+/// Part a node plays in document replication
+#[derive(Clone)]
+pub enum ReplicationRole {
+    Primary(Arc<ReplicationPrimary>),
+    Replica(Arc<ReplicaFollower>),
+}
+
+impl std::fmt::Debug for ReplicationRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Primary(primary) => f.debug_struct("Primary").field("sequence", &primary.sequence()).finish(),
+            Self::Replica(follower) => f.debug_struct("Replica").field("primary", &follower.replica().primary_address()).finish(),
+        }
+    }
+}
+
+impl ReplicationRole {
+    /// Collection manager for this node's documents; a replica's refuses writes
+    pub fn collection_manager(&self) -> CollectionManager {
+        match self {
+            Self::Primary(primary) => primary.collection_manager(),
+            Self::Replica(follower) => follower.replica().collection_manager(),
+        }
+    }
+
+    /// End the change streams a primary serves, so a graceful shutdown does not wait on connected replicas
+    pub fn close_streams(&self) {
+        if let Self::Primary(primary) = self {
+            primary.closed.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TrackedReplica {
+    address: String,
+    applied_sequence: u64,
+    connected: bool,
+    last_seen_ms: u64,
+}
+
+/// Serves a primary's change feed and snapshots and tracks the replicas following it
+pub struct ReplicationPrimary {
+    storage: Arc<ChangeFeedStorage>,
+    replicas: Mutex<HashMap<String, TrackedReplica>>,
+    /// Set on shutdown to end every change stream
+    closed: AtomicBool,
+}
+
+impl ReplicationPrimary {
+    pub fn new(storage: Arc<ChangeFeedStorage>) -> Self {
+        Self {
+            storage,
+            replicas: Mutex::new(HashMap::new()),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    /// Collection manager whose writes are streamed to replicas
+    pub fn collection_manager(&self) -> CollectionManager {
+        CollectionManager::new(self.storage.clone())
+    }
+
+    /// Latest sequence in the change feed
+    pub fn sequence(&self) -> u64 {
+        self.storage.feed().last_sequence()
+    }
+
+    /// Stream the changes after each progress report's applied sequence until the replica goes away
+    pub fn stream_changes<S>(self: &Arc<Self>, mut inbound: S) -> ReceiverStream<Result<ChangeBatch, Status>>
+    where
+        S: Stream<Item = Result<ReplicaProgress, Status>> + Send + Unpin + 'static,
+    {
+        let (tx, rx) = mpsc::channel(4);
+        let primary = self.clone();
+        tokio::spawn(async move {
+            let Some(Ok(first)) = inbound.next().await else {
+                return;
+            };
+            let replica_id = first.replica_id.clone();
+            let feed = primary.storage.feed().clone();
+            // A replica of an earlier primary process may be ahead of this feed
+            if first.applied_sequence > feed.last_sequence() {
+                let _ = tx
+                    .send(Err(Status::out_of_range(format!(
+                        "Replica applied sequence {} but the primary's change feed ends at {}; bootstrap from a new snapshot",
+                        first.applied_sequence,
+                        feed.last_sequence()
+                    ))))
+                    .await;
+                return;
+            }
+            primary.report(&first);
+            info!(replica = %replica_id, address = %first.address, applied = first.applied_sequence, "Replica connected");
+
+            let reports = {
+                let primary = primary.clone();
+                tokio::spawn(async move {
+                    while let Some(Ok(progress)) = inbound.next().await {
+                        primary.report(&progress);
+                    }
+                })
+            };
+
+            let mut sent = first.applied_sequence;
+            while !reports.is_finished() && !tx.is_closed() && !primary.closed.load(Ordering::Relaxed) {
+                let waiting = feed.clone();
+                if !tokio::task::spawn_blocking(move || waiting.wait_after(sent, FEED_POLL_INTERVAL)).await.unwrap_or(false) {
+                    continue;
+                }
+
+                let batch = feed.read_after(sent, MAX_BATCH_RECORDS).and_then(|records| {
+                    sent = records.last().map_or(sent, |record| record.sequence);
+                    records.iter().map(|record| serde_json::to_vec(record).map_err(DocumentError::from)).collect::<Result<Vec<_>, _>>()
+                });
+                let message = batch.map(|records| ChangeBatch {
+                    records,
+                    primary_sequence: feed.last_sequence(),
+                });
+                let failed = message.is_err();
+                if tx.send(message.map_err(replication_status)).await.is_err() || failed {
+                    break;
+                }
+            }
+
+            reports.abort();
+            primary.disconnect(&replica_id);
+            info!(replica = %replica_id, "Replica disconnected");
+        });
+        ReceiverStream::new(rx)
+    }
+
+    /// A snapshot consistent with the change feed, split into chunks
+    pub async fn snapshot_chunks(&self) -> Result<Vec<SnapshotChunk>, Status> {
+        let storage = self.storage.clone();
+        let (summary, data) = tokio::task::spawn_blocking(move || {
+            let mut data = Vec::new();
+            storage.export_snapshot(&mut data).map(|summary| (summary, data))
+        })
+        .await
+        .map_err(|e| Status::internal(format!("Snapshot export failed: {e}")))?
+        .map_err(replication_status)?;
+
+        info!(sequence = summary.sequence, documents = summary.documents, bytes = data.len(), "Exported replication snapshot");
+        Ok(data
+            .chunks(SNAPSHOT_CHUNK_BYTES)
+            .map(|chunk| SnapshotChunk {
+                sequence: summary.sequence,
+                data: chunk.to_vec(),
+            })
+            .collect())
+    }
+
+    /// Fail unless every change up to `sequence` exists
+    pub fn check_sequence(&self, sequence: u64) -> Result<(), Status> {
+        let last = self.sequence();
+        if sequence > last {
+            return Err(Status::unavailable(format!("Primary is at sequence {last}, behind the requested {sequence}")));
+        }
+        Ok(())
+    }
+
+    /// Status of the primary and the lag of every replica that has connected
+    pub fn status(&self) -> GetReplicationStatusResponse {
+        let feed = self.storage.feed();
+        let mut replicas: Vec<ReplicaStatus> = self
+            .replicas
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(replica_id, replica)| {
+                let lag = feed.lag(replica.applied_sequence);
+                ReplicaStatus {
+                    replica_id: replica_id.clone(),
+                    address: replica.address.clone(),
+                    applied_sequence: replica.applied_sequence,
+                    lag_records: lag.records,
+                    lag_seconds: lag.seconds,
+                    connected: replica.connected,
+                    last_seen_ms: replica.last_seen_ms,
+                }
+            })
+            .collect();
+        replicas.sort_by(|a, b| a.replica_id.cmp(&b.replica_id));
+
+        GetReplicationStatusResponse {
+            role: "primary".to_string(),
+            primary_address: String::new(),
+            sequence: feed.last_sequence(),
+            replicas,
+            connected: true,
+        }
+    }
+
+    fn report(&self, progress: &ReplicaProgress) {
+        self.replicas.lock().unwrap_or_else(|e| e.into_inner()).insert(
+            progress.replica_id.clone(),
+            TrackedReplica {
+                address: progress.address.clone(),
+                applied_sequence: progress.applied_sequence,
+                connected: true,
+                last_seen_ms: now_ms(),
+            },
+        );
+    }
+
+    fn disconnect(&self, replica_id: &str) {
+        if let Some(replica) = self.replicas.lock().unwrap_or_else(|e| e.into_inner()).get_mut(replica_id) {
+            replica.connected = false;
+        }
+    }
+}
+
+/// Keeps a [`Replica`] following its primary
+pub struct ReplicaFollower {
+    replica: Arc<Replica>,
+    replica_id: String,
+    /// Address this node serves reads on, reported to the primary
+    address: String,
+    connected: AtomicBool,
+    /// Bearer token for primaries that require an API key
+    authorization: Option<MetadataValue<Ascii>>,
+}
+
+impl ReplicaFollower {
+    pub fn new(replica: Arc<Replica>, replica_id: impl Into<String>, address: impl Into<String>) -> Self {
+        Self {
+            replica,
+            replica_id: replica_id.into(),
+            address: address.into(),
+            connected: AtomicBool::new(false),
+            authorization: None,
+        }
+    }
+
+    /// Present `key` to the primary on every call
+    pub fn with_api_key(mut self, key: &str) -> Self {
+        match MetadataValue::try_from(format!("Bearer {key}")) {
+            Ok(value) => self.authorization = Some(value),
+            Err(_) => warn!("Replication API key is not valid header text, connecting without it"),
+        }
+        self
+    }
+
+    fn request<T>(&self, message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        if let Some(authorization) = &self.authorization {
+            request.metadata_mut().insert("authorization", authorization.clone());
+        }
+        request
+    }
+
+    pub fn replica(&self) -> &Arc<Replica> {
+        &self.replica
+    }
+
+    /// Whether the change stream from the primary is currently open
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Follow the primary in the background, reconnecting until the task is aborted
+    pub fn spawn(self: &Arc<Self>) -> JoinHandle<()> {
+        let follower = self.clone();
+        tokio::spawn(async move { follower.run().await })
+    }
+
+    async fn run(&self) {
+        let mut needs_snapshot = true;
+        let mut backoff = MIN_RECONNECT_BACKOFF;
+        loop {
+            let result = self.follow(&mut needs_snapshot).await;
+            // A stream that got going earns a quick reconnect
+            if self.connected.swap(false, Ordering::Relaxed) {
+                backoff = MIN_RECONNECT_BACKOFF;
+            }
+            match result {
+                Ok(()) => info!(primary = %self.replica.primary_address(), "Primary closed the change stream"),
+                Err(status) => {
+                    if status.code() == tonic::Code::OutOfRange {
+                        needs_snapshot = true;
+                    }
+                    warn!(primary = %self.replica.primary_address(), error = %status.message(), retry_in_ms = backoff.as_millis() as u64, "Replication stream failed");
+                }
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_RECONNECT_BACKOFF);
+        }
+    }
+
+    /// Connect, bootstrap if needed and apply changes until the stream ends
+    async fn follow(&self, needs_snapshot: &mut bool) -> Result<(), Status> {
+        let endpoint = endpoint(&self.replica.primary_address());
+        let mut client = DatabaseServiceClient::connect(endpoint.clone())
+            .await
+            .map_err(|e| Status::unavailable(format!("Cannot reach primary at {endpoint}: {e}")))?;
+
+        if *needs_snapshot {
+            let mut chunks = client.get_snapshot(self.request(GetSnapshotRequest {})).await?.into_inner();
+            let mut data = Vec::new();
+            while let Some(chunk) = chunks.message().await? {
+                data.extend_from_slice(&chunk.data);
+            }
+            let replica = self.replica.clone();
+            let summary = tokio::task::spawn_blocking(move || replica.bootstrap(data.as_slice()))
+                .await
+                .map_err(|e| Status::internal(format!("Snapshot import failed: {e}")))?
+                .map_err(replication_status)?;
+            info!(sequence = summary.sequence, documents = summary.documents, "Bootstrapped replica from snapshot");
+            *needs_snapshot = false;
+        }
+
+        let (progress, reports) = mpsc::channel(4);
+        progress.send(self.progress()).await.map_err(|_| Status::cancelled("Replication stream closed"))?;
+        let mut changes = client.replicate_changes(self.request(ReceiverStream::new(reports))).await?.into_inner();
+        self.connected.store(true, Ordering::Relaxed);
+        info!(primary = %endpoint, applied = self.replica.applied_sequence(), "Streaming changes from primary");
+
+        while let Some(batch) = changes.message().await? {
+            let records = batch
+                .records
+                .iter()
+                .map(|record| serde_json::from_slice::<ChangeRecord>(record))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| Status::data_loss(format!("Undecodable change record: {e}")))?;
+            let replica = self.replica.clone();
+            tokio::task::spawn_blocking(move || records.iter().try_for_each(|record| replica.apply(record).map(|_| ())))
+                .await
+                .map_err(|e| Status::internal(format!("Applying changes failed: {e}")))?
+                .map_err(replication_status)?;
+            if progress.send(self.progress()).await.is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn progress(&self) -> ReplicaProgress {
+        ReplicaProgress {
+            replica_id: self.replica_id.clone(),
+            address: self.address.clone(),
+            applied_sequence: self.replica.applied_sequence(),
+        }
+    }
+
+    /// Wait up to `timeout` until the replica has applied `sequence`
+    pub async fn wait_for_sequence(&self, sequence: u64, timeout: Duration) -> Result<(), Status> {
+        let replica = self.replica.clone();
+        tokio::task::spawn_blocking(move || replica.wait_for_sequence(sequence, timeout))
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .map_err(replication_status)
+    }
+
+    /// Applied sequence and connection state of the replica
+    pub fn status(&self) -> GetReplicationStatusResponse {
+        GetReplicationStatusResponse {
+            role: "replica".to_string(),
+            primary_address: self.replica.primary_address(),
+            sequence: self.replica.applied_sequence(),
+            replicas: Vec::new(),
+            connected: self.is_connected(),
+        }
+    }
+
+    /// Error returned for writes sent to this replica
+    pub fn read_only_status(&self) -> Status {
+        replication_status(DocumentError::ReadOnlyReplica {
+            primary: self.replica.primary_address(),
+        })
+    }
+}
+
+/// gRPC status for a replication error; refused writes carry the primary in [`PRIMARY_METADATA_KEY`]
+pub fn replication_status(error: DocumentError) -> Status {
+    match &error {
+        DocumentError::ReadOnlyReplica { primary } => {
+            let mut status = Status::failed_precondition(error.to_string());
+            if let Ok(value) = MetadataValue::try_from(primary.as_str()) {
+                status.metadata_mut().insert(PRIMARY_METADATA_KEY, value);
+            }
+            status
+        }
+        DocumentError::ReplicaBehind { .. } => Status::unavailable(error.to_string()),
+        DocumentError::ChangeFeedTruncated { .. } | DocumentError::ReplicationGap { .. } => Status::out_of_range(error.to_string()),
+        _ => Status::internal(error.to_string()),
+    }
+}
+
+/// URI for a primary address; addresses without a scheme are reached over plaintext HTTP/2
+fn endpoint(address: &str) -> String {
+    if address.contains("://") { address.to_string() } else { format!("http://{address}") }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dotdb_core::document::{ChangeFeed, create_in_memory_collection_manager};
+    use serde_json::json;
+
+    fn primary() -> Arc<ReplicationPrimary> {
+        let documents = create_in_memory_collection_manager().unwrap();
+        Arc::new(ReplicationPrimary::new(Arc::new(ChangeFeedStorage::new(documents.storage().clone(), Arc::new(ChangeFeed::default())))))
+    }
+
+    fn progress(applied_sequence: u64) -> Result<ReplicaProgress, Status> {
+        Ok(ReplicaProgress {
+            replica_id: "replica-1".to_string(),
+            address: "10.0.0.2:50051".to_string(),
+            applied_sequence,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_stream_resumes_after_applied_sequence() {
+        let primary = primary();
+        let manager = primary.collection_manager();
+        for i in 0..3 {
+            manager.insert_value("events", json!({"n": i})).unwrap();
+        }
+
+        let (reports, inbound) = mpsc::channel(4);
+        reports.send(progress(1)).await.unwrap();
+        let mut changes = primary.stream_changes(ReceiverStream::new(inbound));
+        let batch = changes.next().await.unwrap().unwrap();
+        let sequences: Vec<u64> = batch.records.iter().map(|record| serde_json::from_slice::<ChangeRecord>(record).unwrap().sequence).collect();
+        assert_eq!(sequences, [2, 3]);
+        assert_eq!(batch.primary_sequence, 3);
+
+        let status = primary.status();
+        assert_eq!(status.replicas.len(), 1);
+        assert_eq!((status.replicas[0].applied_sequence, status.replicas[0].lag_records), (1, 2));
+        assert!(status.replicas[0].connected);
+
+        reports.send(progress(3)).await.unwrap();
+        manager.insert_value("events", json!({"n": 3})).unwrap();
+        assert_eq!(changes.next().await.unwrap().unwrap().records.len(), 1);
+
+        drop(reports);
+        assert!(changes.next().await.is_none());
+        assert!(!primary.status().replicas[0].connected);
+    }
+
+    #[tokio::test]
+    async fn test_replica_ahead_of_feed_must_bootstrap() {
+        let primary = primary();
+        let (reports, inbound) = mpsc::channel(1);
+        reports.send(progress(10)).await.unwrap();
+        let error = primary.stream_changes(ReceiverStream::new(inbound)).next().await.unwrap().unwrap_err();
+        assert_eq!(error.code(), tonic::Code::OutOfRange);
+    }
+
+    #[test]
+    fn test_refused_writes_name_the_primary() {
+        let documents = create_in_memory_collection_manager().unwrap();
+        let follower = ReplicaFollower::new(Arc::new(Replica::new(documents.storage().clone(), "10.0.0.1:50051")), "replica-1", "10.0.0.2:50051");
+        let status = follower.read_only_status();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+        assert!(status.message().contains("10.0.0.1:50051"));
+        assert_eq!(status.metadata().get(PRIMARY_METADATA_KEY).unwrap(), "10.0.0.1:50051");
+        assert_eq!(endpoint("10.0.0.1:50051"), "http://10.0.0.1:50051");
+    }
+}
@@ -32,21 +32,138 @@ use uuid::Uuid;
 
 // Import proto types
 use crate::proto::vm_service::{DotEvent, StreamDotEventsRequest, VmMetric};
-
-// Add missing types for vm_service.rs compatibility
-pub struct DotEventBroadcaster;
+use crate::services::abi::events::{EVENT_VERSION_METADATA, EventSchemaStrictness, SCHEMA_VIOLATION_METADATA, check_event};
+use crate::services::abi::registry::AbiRegistry;
+use dotdb_core::state::db_interface::DatabaseInterface;
+use dotvm_runtime::events::bus::DEFAULT_LIVE_BUFFER;
+use dotvm_runtime::events::{DurableEvent, EventBus, EventDispatcher, EventLog, EventQueue, EventRecord, RetentionPolicy};
+
+/// Dot event broadcaster backed by the runtime event bus
+///
+/// Every dot event is appended to the persistent event log, so a subscriber
+/// that reconnects with a `from_sequence` replays what it missed before
+/// switching to live events. With an ABI registry attached, published events
+/// are checked against the event schemas their dot declares.
+pub struct DotEventBroadcaster {
+    bus: Arc<EventBus>,
+    schemas: Option<Arc<AbiRegistry>>,
+    strictness: EventSchemaStrictness,
+}
 
 impl DotEventBroadcaster {
+    /// Create a broadcaster whose event log lives in memory
     pub fn new() -> Self {
-        Self
+        let bus = EventBus::in_memory(RetentionPolicy::default()).expect("in-memory event log cannot fail to open");
+        Self::with_bus(Arc::new(bus))
+    }
+
+    /// Create a broadcaster whose event log is stored in `db`
+    pub fn open(db: Arc<dyn DatabaseInterface>, retention: RetentionPolicy) -> Result<Self, String> {
+        let log = EventLog::open(db, retention).map_err(|e| format!("Failed to open event log: {}", e))?;
+        let dispatcher = EventDispatcher::new(Arc::new(std::sync::Mutex::new(EventQueue::new())));
+        Ok(Self::with_bus(Arc::new(EventBus::new(Arc::new(dispatcher), Arc::new(log), DEFAULT_LIVE_BUFFER))))
     }
 
-    pub async fn subscribe<F>(&self, _subscriber_id: String, _filter: F) -> impl Stream<Item = Result<DotEvent, Status>> + 'static
+    /// Create a broadcaster over an existing event bus
+    pub fn with_bus(bus: Arc<EventBus>) -> Self {
+        Self {
+            bus,
+            schemas: None,
+            strictness: EventSchemaStrictness::Off,
+        }
+    }
+
+    /// Check published events against the event schemas declared in `registry`
+    pub fn with_schemas(mut self, registry: Arc<AbiRegistry>, strictness: EventSchemaStrictness) -> Self {
+        self.schemas = Some(registry);
+        self.strictness = strictness;
+        self
+    }
+
+    /// The underlying event bus
+    pub fn bus(&self) -> &Arc<EventBus> {
+        &self.bus
+    }
+
+    /// Persist a dot event and deliver it to subscribers, returning its sequence number
+    ///
+    /// An event that does not match its declared schema is refused or flagged, as the
+    /// broadcaster's strictness says.
+    pub fn publish(&self, mut event: DurableEvent) -> Result<u64, Status> {
+        if let Some(violation) = self.schema_violation(&event)? {
+            if self.strictness == EventSchemaStrictness::Reject {
+                return Err(Status::invalid_argument(format!(
+                    "Event {} of dot {} does not match its schema: {}",
+                    event.event_type, event.dot_id, violation
+                )));
+            }
+            warn!("Event {} of dot {} does not match its schema: {}", event.event_type, event.dot_id, violation);
+            event.metadata.insert(SCHEMA_VIOLATION_METADATA.to_string(), violation);
+        }
+
+        self.bus
+            .publish_durable(event)
+            .map(|record| record.sequence)
+            .map_err(|e| Status::internal(format!("Failed to persist dot event: {}", e)))
+    }
+
+    /// Why an event does not match the schema its dot declares, if it is checked at all
+    fn schema_violation(&self, event: &DurableEvent) -> Result<Option<String>, Status> {
+        let Some(registry) = self.schemas.as_ref().filter(|_| self.strictness != EventSchemaStrictness::Off) else {
+            return Ok(None);
+        };
+        let declared = registry
+            .declared_events(&event.dot_id)
+            .map_err(|e| Status::internal(format!("Failed to look up event schemas: {}", e)))?;
+        let version = match event.metadata.get(EVENT_VERSION_METADATA) {
+            Some(version) => match version.parse::<u32>() {
+                Ok(version) => Some(version),
+                Err(_) => return Ok(Some(format!("{} '{}' is not a version number", EVENT_VERSION_METADATA, version))),
+            },
+            None => None,
+        };
+        Ok(check_event(&declared, &event.event_type, version, &event.payload).err())
+    }
+
+    /// Subscribe to dot events accepted by `filter`
+    ///
+    /// A `from_sequence` of zero delivers live events only; otherwise retained
+    /// events from that sequence number onwards are replayed first.
+    pub async fn subscribe<F>(&self, subscriber_id: String, filter: F, from_sequence: u64) -> impl Stream<Item = Result<DotEvent, Status>> + 'static
     where
         F: Fn(&DotEvent) -> bool + Send + Sync + 'static,
     {
-        // Return empty stream for now
-        futures::stream::empty()
+        debug!("Subscriber {} attaching at sequence {}", subscriber_id, from_sequence);
+
+        let subscription = if from_sequence == 0 {
+            self.bus.subscribe_live(|_| true)
+        } else {
+            self.bus.subscribe_from(from_sequence, |_| true)
+        };
+
+        subscription.filter_map(move |item| {
+            let item = match item {
+                Ok(record) => {
+                    let event = dot_event_from_record(record);
+                    filter(&event).then_some(Ok(event))
+                }
+                Err(e) => Some(Err(Status::internal(format!("Failed to read event log: {}", e)))),
+            };
+            futures::future::ready(item)
+        })
+    }
+}
+
+/// Convert a persisted event record into its gRPC form
+fn dot_event_from_record(record: EventRecord) -> DotEvent {
+    DotEvent {
+        event_id: record.sequence.to_string(),
+        dot_id: record.event.dot_id,
+        event_type: record.event.event_type,
+        timestamp: record.timestamp_ms / 1000,
+        event_data: record.event.payload,
+        metadata: record.event.metadata,
+        sequence: record.sequence,
     }
 }
 
@@ -70,10 +187,14 @@ impl VmMetricsCollector {
 pub mod dot_events {
     use super::*;
 
+    use crate::proto::vm_service::PayloadPredicate;
+    use serde_json::Value;
+
     pub fn create_filter_from_request(req: &StreamDotEventsRequest) -> Box<dyn Fn(&DotEvent) -> bool + Send + Sync> {
         // Simple filter implementation
         let dot_ids = req.dot_ids.clone();
         let event_types = req.event_types.clone();
+        let predicates: Vec<(Vec<String>, Value)> = req.payload_filters.iter().map(parse_predicate).collect();
 
         Box::new(move |event: &DotEvent| {
             if !dot_ids.is_empty() && !dot_ids.contains(&event.dot_id) {
@@ -82,9 +203,39 @@ pub mod dot_events {
             if !event_types.is_empty() && !event_types.contains(&event.event_type) {
                 return false;
             }
-            true
+            predicates.is_empty() || payload_matches(&event.event_data, &predicates)
         })
     }
+
+    /// Splits a predicate into its field path and the value the field must equal
+    ///
+    /// The value is read as a JSON literal, so `42` matches a number and `"42"` a string;
+    /// text that is not valid JSON, like `shipped`, is compared as a string.
+    fn parse_predicate(predicate: &PayloadPredicate) -> (Vec<String>, Value) {
+        let path = predicate.field.split('.').map(str::to_string).collect();
+        let value = serde_json::from_str(&predicate.equals).unwrap_or_else(|_| Value::String(predicate.equals.clone()));
+        (path, value)
+    }
+
+    /// Whether a JSON payload has every predicate's value at its path; other payloads match none
+    fn payload_matches(payload: &[u8], predicates: &[(Vec<String>, Value)]) -> bool {
+        let Ok(payload) = serde_json::from_slice::<Value>(payload) else {
+            return false;
+        };
+        predicates
+            .iter()
+            .all(|(path, expected)| path.iter().try_fold(&payload, |value, key| value.get(key)).is_some_and(|value| value == expected))
+    }
+
+    /// Sequence number a stream request resumes from (zero for live events only)
+    ///
+    /// An explicit `from_sequence` wins; otherwise a numeric `last_event_id` resumes just after that event.
+    pub fn resume_sequence(req: &StreamDotEventsRequest) -> u64 {
+        if req.from_sequence > 0 {
+            return req.from_sequence;
+        }
+        req.last_event_id.parse::<u64>().map(|id| id + 1).unwrap_or(0)
+    }
 }
 
 /// Stream configuration for advanced features
@@ -499,6 +650,90 @@ impl<T> Drop for ManagedStream<T> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::proto::vm_service::{AbiEvent, AbiField, AbiType, DotAbi, PayloadPredicate, RegisterAbiRequest};
+
+    fn predicate(field: &str, equals: &str) -> PayloadPredicate {
+        PayloadPredicate {
+            field: field.to_string(),
+            equals: equals.to_string(),
+        }
+    }
+
+    fn dot_event(event_type: &str, payload: &str) -> DotEvent {
+        DotEvent {
+            dot_id: "dot-1".to_string(),
+            event_type: event_type.to_string(),
+            event_data: payload.as_bytes().to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_filter_matches_payload_predicates() {
+        let filter = dot_events::create_filter_from_request(&StreamDotEventsRequest {
+            event_types: vec!["order".to_string()],
+            payload_filters: vec![predicate("status", "shipped"), predicate("customer.tier", "2")],
+            ..Default::default()
+        });
+
+        assert!(filter(&dot_event("order", r#"{"status": "shipped", "customer": {"tier": 2}}"#)));
+        assert!(!filter(&dot_event("order", r#"{"status": "shipped", "customer": {"tier": "2"}}"#)));
+        assert!(!filter(&dot_event("order", r#"{"status": "pending", "customer": {"tier": 2}}"#)));
+        assert!(!filter(&dot_event("order", r#"{"status": "shipped"}"#)));
+        assert!(!filter(&dot_event("order", "not json")));
+        assert!(!filter(&dot_event("refund", r#"{"status": "shipped", "customer": {"tier": 2}}"#)));
+
+        // Without predicates the payload is not inspected
+        let filter = dot_events::create_filter_from_request(&StreamDotEventsRequest::default());
+        assert!(filter(&dot_event("order", "not json")));
+    }
+
+    #[tokio::test]
+    async fn test_published_events_are_checked_against_their_schema() {
+        let registry = Arc::new(AbiRegistry::new());
+        let amount = AbiField {
+            name: "amount".to_string(),
+            field_type: Some(AbiType {
+                type_name: "Integer".to_string(),
+                ..Default::default()
+            }),
+            required: true,
+            ..Default::default()
+        };
+        let abi = DotAbi {
+            dot_name: "wallet".to_string(),
+            version: "1.0.0".to_string(),
+            events: vec![AbiEvent {
+                name: "deposited".to_string(),
+                version: 1,
+                fields: vec![amount],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let registered = registry
+            .register_abi(RegisterAbiRequest {
+                dot_id: "dot-1".to_string(),
+                abi: Some(abi),
+                registrar_id: "tester".to_string(),
+            })
+            .await
+            .unwrap();
+        assert!(registered.success);
+
+        let strict = DotEventBroadcaster::new().with_schemas(Arc::clone(&registry), EventSchemaStrictness::Reject);
+        assert!(strict.publish(DurableEvent::new("dot-1", "deposited", br#"{"amount": 5}"#.to_vec())).is_ok());
+        let refused = strict.publish(DurableEvent::new("dot-1", "deposited", br#"{"amount": "five"}"#.to_vec())).unwrap_err();
+        assert_eq!(refused.code(), tonic::Code::InvalidArgument);
+        assert!(strict.publish(DurableEvent::new("dot-1", "withdrawn", b"{}".to_vec())).is_err());
+        assert!(strict.publish(DurableEvent::new("dot-2", "anything", b"raw".to_vec())).is_ok());
+
+        let flagging = DotEventBroadcaster::new().with_schemas(registry, EventSchemaStrictness::Flag);
+        let sequence = flagging.publish(DurableEvent::new("dot-1", "deposited", br#"{"amount": "five"}"#.to_vec())).unwrap();
+        let mut events = Box::pin(flagging.subscribe("tester".to_string(), |_: &DotEvent| true, sequence).await);
+        let flagged = events.next().await.unwrap().unwrap();
+        assert_eq!(flagged.metadata.get(SCHEMA_VIOLATION_METADATA).map(String::as_str), Some("field amount is not a valid Integer"));
+    }
 
     #[tokio::test]
     async fn test_managed_stream_creation() {
@@ -16,20 +16,32 @@
 
 //! VM management service implementation
 
+use dotvm_core::bytecode::VmArchitecture;
 use std::collections::HashMap;
 use tonic::{Request, Response, Result as TonicResult, Status};
 use tracing::{error, info, instrument};
 
 use crate::proto::vm_service::{ArchitectureInfo, GetArchitecturesRequest, GetArchitecturesResponse, GetVmStatusRequest, GetVmStatusResponse, PerformanceProfile, ResourceUsage, VmInfo, VmStatus};
+use crate::services::dots::architecture::ArchitectureConfig;
 
 /// VM management service handles VM lifecycle and configuration
 pub struct VmManagementService {
     // TODO: Add actual VM management components
+    /// Architectures reported as supported
+    architectures: ArchitectureConfig,
 }
 
 impl VmManagementService {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            architectures: ArchitectureConfig::default(),
+        }
+    }
+
+    /// Report the configured architectures instead of the built-in ones
+    pub fn with_architectures(mut self, architectures: ArchitectureConfig) -> Self {
+        self.architectures = architectures;
+        self
     }
 
     #[instrument(skip(self, request))]
@@ -43,10 +55,10 @@ impl VmManagementService {
             status: VmStatus::Running as i32,
             info: Some(VmInfo {
                 version: "0.1.0".to_string(),
-                architecture: "arch64".to_string(),
+                architecture: self.architectures.default.to_string(),
                 uptime_seconds: 3600, // 1 hour
                 dots_count: 5,
-                paradots_count: 3,
+                paradots_count: 0, // ParaDots are counted by the dots service
                 resource_usage: Some(ResourceUsage {
                     memory_used_bytes: 1024 * 1024 * 100,   // 100MB
                     memory_total_bytes: 1024 * 1024 * 1024, // 1GB
@@ -56,7 +68,8 @@ impl VmManagementService {
                 }),
             }),
             active_dots: vec!["dot_12345678".to_string()],
-            active_paradots: vec!["paradot_87654321".to_string()],
+            active_paradots: vec![],
+            paradots: vec![],
         };
 
         Ok(Response::new(response))
@@ -68,35 +81,44 @@ impl VmManagementService {
 
         info!("Getting supported architectures");
 
-        let architectures = vec![
-            ArchitectureInfo {
-                name: "arch64".to_string(),
-                description: "64-bit architecture".to_string(),
-                features: vec!["simd".to_string(), "parallel".to_string()],
-                is_default: true,
-                performance: Some(PerformanceProfile {
-                    optimization_level: "O2".to_string(),
-                    supports_simd: true,
-                    supports_parallel: true,
-                    max_memory_gb: 16,
-                }),
-            },
+        let response = GetArchitecturesResponse {
+            architectures: architecture_infos(&self.architectures),
+        };
+
+        Ok(Response::new(response))
+    }
+}
+
+/// The configured architectures as GetArchitectures reports them, narrowest first
+pub fn architecture_infos(architectures: &ArchitectureConfig) -> Vec<ArchitectureInfo> {
+    let mut supported = architectures.supported.clone();
+    supported.sort();
+
+    supported
+        .into_iter()
+        .map(|architecture| {
+            let wide = architecture >= VmArchitecture::Arch128;
+            let mut features = vec!["parallel".to_string()];
+            if architecture != VmArchitecture::Arch32 {
+                features.insert(0, "simd".to_string());
+            }
+            if wide {
+                features.push("extended".to_string());
+            }
+
             ArchitectureInfo {
-                name: "arch128".to_string(),
-                description: "128-bit architecture".to_string(),
-                features: vec!["simd".to_string(), "parallel".to_string(), "extended".to_string()],
-                is_default: false,
+                name: architecture.to_string(),
+                description: format!("{}-bit architecture", architecture.bit_width()),
+                features,
+                is_default: architecture == architectures.default,
                 performance: Some(PerformanceProfile {
-                    optimization_level: "O3".to_string(),
-                    supports_simd: true,
+                    optimization_level: if wide { "O3" } else { "O2" }.to_string(),
+                    supports_simd: architecture != VmArchitecture::Arch32,
                     supports_parallel: true,
-                    max_memory_gb: 64,
+                    // 4 GB for 32-bit, quadrupling with every doubling of the word size
+                    max_memory_gb: 4 << (2 * architecture as u32),
                 }),
-            },
-        ];
-
-        let response = GetArchitecturesResponse { architectures };
-
-        Ok(Response::new(response))
-    }
+            }
+        })
+        .collect()
 }
@@ -30,6 +30,7 @@ use uuid::Uuid;
 
 // VM and StateStorage imports - now available
 use dotdb_core::state::db_interface::{Database, DatabaseInterface, DbConfig};
+use dotvm_runtime::events::RetentionPolicy;
 use dotvm_core::bytecode::BytecodeFile;
 use dotvm_core::vm::executor::VmExecutor;
 use dotvm_core::vm::stack::StackValue;
@@ -40,7 +41,8 @@ use dotvm_core::vm::vm_factory::{SimpleVMFactory, VMFactory, VmInstance};
 use crate::proto::vm_service::{vm_service_server::VmService, *};
 use crate::services::streaming;
 
-use super::{AbiService, DotsService, MetricsService, VmManagementService};
+use super::dots::permissions::PermissionEvaluator;
+use super::{AbiService, DotsService, LogsService, MetricsService, VmManagementService};
 
 /// VM Service implementation - coordinates all sub-services
 pub struct VmServiceImpl {
@@ -48,6 +50,7 @@ pub struct VmServiceImpl {
     abi_service: Arc<AbiService>,
     metrics_service: Arc<MetricsService>,
     vm_management_service: Arc<VmManagementService>,
+    logs_service: Arc<LogsService>,
 
     // Advanced gRPC Features
     active_sessions: Arc<RwLock<HashMap<String, InteractiveSession>>>,
@@ -1035,6 +1038,7 @@ impl VmServiceImpl {
             StackValue::Json(json) => json.to_string().as_bytes().to_vec(),
             StackValue::DocumentId(id) => id.as_bytes().to_vec(),
             StackValue::Collection(collection) => collection.as_bytes().to_vec(),
+            StackValue::Wide(value) => value.value().to_string().as_bytes().to_vec(),
         }
     }
 
@@ -1079,18 +1083,22 @@ impl VmServiceImpl {
         // Initialize VM factory
         let vm_factory = Arc::new(SimpleVMFactory::new());
 
-        // Initialize shared streaming components
-        let event_broadcaster = Arc::new(streaming::DotEventBroadcaster::new());
+        // Initialize shared streaming components; dot events are persisted alongside VM state
+        let event_broadcaster = Arc::new(streaming::DotEventBroadcaster::open(database.clone(), RetentionPolicy::default())?);
         let metrics_collector = Arc::new(streaming::VmMetricsCollector::new());
 
-        // Start background metrics collection
+        // Start background metrics collection and event log retention
         metrics_collector.start().await;
+        event_broadcaster.bus().start_retention_task(Duration::from_secs(60));
 
+        // Executions are checked against the permissions of the ABIs registered with the ABI service
+        let abi_service = Arc::new(AbiService::new());
         Ok(Self {
-            dots_service: Arc::new(DotsService::new()),
-            abi_service: Arc::new(AbiService::new()),
+            dots_service: Arc::new(DotsService::new().with_permissions(PermissionEvaluator::new(abi_service.registry()))),
+            abi_service,
             metrics_service: Arc::new(MetricsService::new()),
             vm_management_service: Arc::new(VmManagementService::new()),
+            logs_service: Arc::new(LogsService::new()),
 
             active_sessions: Arc::new(RwLock::new(HashMap::new())),
             debug_sessions: Arc::new(RwLock::new(HashMap::new())),
@@ -1114,14 +1122,16 @@ impl VmServiceImpl {
         let vm_factory = Arc::new(SimpleVMFactory::new());
 
         // Initialize shared streaming components
-        let event_broadcaster = Arc::new(streaming::DotEventBroadcaster::new());
+        let event_broadcaster = Arc::new(streaming::DotEventBroadcaster::open(database.clone(), RetentionPolicy::default())?);
         let metrics_collector = Arc::new(streaming::VmMetricsCollector::new());
 
+        let abi_service = Arc::new(AbiService::new());
         Ok(Self {
-            dots_service: Arc::new(DotsService::new()),
-            abi_service: Arc::new(AbiService::new()),
+            dots_service: Arc::new(DotsService::new().with_permissions(PermissionEvaluator::new(abi_service.registry()))),
+            abi_service,
             metrics_service: Arc::new(MetricsService::new()),
             vm_management_service: Arc::new(VmManagementService::new()),
+            logs_service: Arc::new(LogsService::new()),
 
             active_sessions: Arc::new(RwLock::new(HashMap::new())),
             debug_sessions: Arc::new(RwLock::new(HashMap::new())),
@@ -1323,12 +1333,31 @@ impl VmService for VmServiceImpl {
         self.dots_service.deploy_dot(request).await
     }
 
+    #[instrument(skip(self, request))]
+    async fn deploy_dot_stream(&self, request: Request<Streaming<DeployDotChunk>>) -> TonicResult<Response<DeployDotResponse>> {
+        self.dots_service.deploy_dot_stream(request).await
+    }
+
+    type DeployDotWithProgressStream = std::pin::Pin<Box<dyn futures::Stream<Item = Result<DeployDotProgress, Status>> + Send>>;
+
+    #[instrument(skip(self, request))]
+    async fn deploy_dot_with_progress(&self, request: Request<DeployDotRequest>) -> TonicResult<Response<Self::DeployDotWithProgressStream>> {
+        let response = self.dots_service.deploy_dot_with_progress(request)?;
+        Ok(response.map(|stream| Box::pin(stream) as Self::DeployDotWithProgressStream))
+    }
+
     #[instrument(skip(self, request))]
     async fn get_dot_state(&self, request: Request<GetDotStateRequest>) -> TonicResult<Response<GetDotStateResponse>> {
         // Delegate to dots service
         self.dots_service.get_dot_state(request).await
     }
 
+    #[instrument(skip(self, request))]
+    async fn get_state_diff(&self, request: Request<GetStateDiffRequest>) -> TonicResult<Response<GetStateDiffResponse>> {
+        // Delegate to dots service
+        self.dots_service.get_state_diff(request).await
+    }
+
     #[instrument(skip(self, request))]
     async fn list_dots(&self, request: Request<ListDotsRequest>) -> TonicResult<Response<ListDotsResponse>> {
         // Delegate to dots service
@@ -1337,8 +1366,49 @@ impl VmService for VmServiceImpl {
 
     #[instrument(skip(self, request))]
     async fn delete_dot(&self, request: Request<DeleteDotRequest>) -> TonicResult<Response<DeleteDotResponse>> {
+        // Delegate to dots service, keeping the deleted dot's ABI history archived
+        let dot_id = request.get_ref().dot_id.clone();
+        let response = self.dots_service.delete_dot(request).await?;
+        if response.get_ref().success {
+            self.abi_service.archive_dot(&dot_id).await;
+        }
+        Ok(response)
+    }
+
+    #[instrument(skip(self, request))]
+    async fn activate_dot_version(&self, request: Request<ActivateDotVersionRequest>) -> TonicResult<Response<ActivateDotVersionResponse>> {
         // Delegate to dots service
-        self.dots_service.delete_dot(request).await
+        self.dots_service.activate_dot_version(request).await
+    }
+
+    #[instrument(skip(self, request))]
+    async fn set_dot_quota(&self, request: Request<SetDotQuotaRequest>) -> TonicResult<Response<SetDotQuotaResponse>> {
+        self.dots_service.set_dot_quota(request).await
+    }
+
+    #[instrument(skip(self, request))]
+    async fn get_dot_quota(&self, request: Request<GetDotQuotaRequest>) -> TonicResult<Response<GetDotQuotaResponse>> {
+        self.dots_service.get_dot_quota(request).await
+    }
+
+    #[instrument(skip(self, request))]
+    async fn set_dot_caching(&self, request: Request<SetDotCachingRequest>) -> TonicResult<Response<SetDotCachingResponse>> {
+        self.dots_service.set_dot_caching(request).await
+    }
+
+    #[instrument(skip(self, request))]
+    async fn get_dot_cache_stats(&self, request: Request<GetDotCacheStatsRequest>) -> TonicResult<Response<GetDotCacheStatsResponse>> {
+        self.dots_service.get_dot_cache_stats(request).await
+    }
+
+    #[instrument(skip(self, request))]
+    async fn list_checkpoints(&self, request: Request<ListCheckpointsRequest>) -> TonicResult<Response<ListCheckpointsResponse>> {
+        self.dots_service.list_checkpoints(request).await
+    }
+
+    #[instrument(skip(self, request))]
+    async fn restore_checkpoint(&self, request: Request<RestoreCheckpointRequest>) -> TonicResult<Response<RestoreCheckpointResponse>> {
+        self.dots_service.restore_checkpoint(request).await
     }
 
     #[instrument(skip(self, request))]
@@ -1431,14 +1501,50 @@ impl VmService for VmServiceImpl {
         self.abi_service.register_abi(request).await
     }
 
-    // ParaDot operations removed - they are automatically managed during dot execution
-    // ParaDots are spawned and coordinated internally based on dot requirements
+    #[instrument(skip(self, request))]
+    async fn list_abi_versions(&self, request: Request<ListAbiVersionsRequest>) -> TonicResult<Response<ListAbiVersionsResponse>> {
+        // Delegate to ABI service
+        self.abi_service.list_abi_versions(request).await
+    }
+
+    // ParaDots are also spawned and coordinated internally during dot execution
     // See dots/paradots/ module for ParaDot management implementation
 
+    #[instrument(skip(self, request))]
+    async fn spawn_para_dot(&self, request: Request<SpawnParaDotRequest>) -> TonicResult<Response<SpawnParaDotResponse>> {
+        self.dots_service.spawn_paradot(request).await
+    }
+
+    #[instrument(skip(self, request))]
+    async fn list_para_dots(&self, request: Request<ListParaDotsRequest>) -> TonicResult<Response<ListParaDotsResponse>> {
+        self.dots_service.list_paradots(request).await
+    }
+
+    #[instrument(skip(self, request))]
+    async fn execute_para_dot(&self, request: Request<ExecuteParaDotRequest>) -> TonicResult<Response<ExecuteParaDotResponse>> {
+        self.dots_service.execute_paradot(request).await
+    }
+
+    #[instrument(skip(self, request))]
+    async fn terminate_para_dot(&self, request: Request<TerminateParaDotRequest>) -> TonicResult<Response<TerminateParaDotResponse>> {
+        self.dots_service.terminate_paradot(request).await
+    }
+
     #[instrument(skip(self, request))]
     async fn get_vm_status(&self, request: Request<GetVmStatusRequest>) -> TonicResult<Response<GetVmStatusResponse>> {
-        // Delegate to VM management service
-        self.vm_management_service.get_vm_status(request).await
+        // Delegate to VM management service; ParaDots are tracked by the dots service
+        let include_details = request.get_ref().include_details;
+        let mut response = self.vm_management_service.get_vm_status(request).await?;
+        let status = response.get_mut();
+        let paradots = self.dots_service.paradots();
+        if let Some(info) = status.info.as_mut() {
+            info.paradots_count = paradots.len() as u32;
+        }
+        status.active_paradots = paradots.iter().map(|paradot| paradot.paradot_id.clone()).collect();
+        if include_details {
+            status.paradots = paradots;
+        }
+        Ok(response)
     }
 
     #[instrument(skip(self, request))]
@@ -1447,6 +1553,21 @@ impl VmService for VmServiceImpl {
         self.metrics_service.get_vm_metrics(request).await
     }
 
+    #[instrument(skip(self, request))]
+    async fn set_memory_tracking(&self, request: Request<SetMemoryTrackingRequest>) -> TonicResult<Response<SetMemoryTrackingResponse>> {
+        self.metrics_service.set_memory_tracking(request).await
+    }
+
+    #[instrument(skip(self, request))]
+    async fn query_logs(&self, request: Request<QueryLogsRequest>) -> TonicResult<Response<QueryLogsResponse>> {
+        self.logs_service.query_logs(request).await
+    }
+
+    #[instrument(skip(self, request))]
+    async fn ingest_logs(&self, request: Request<IngestLogsRequest>) -> TonicResult<Response<IngestLogsResponse>> {
+        self.logs_service.ingest_logs(request).await
+    }
+
     #[instrument(skip(self, request))]
     async fn get_architectures(&self, request: Request<GetArchitecturesRequest>) -> TonicResult<Response<GetArchitecturesResponse>> {
         // Delegate to VM management service
@@ -1462,7 +1583,7 @@ impl VmService for VmServiceImpl {
     type LiveDotDebuggingStream = std::pin::Pin<Box<dyn futures::Stream<Item = Result<DebugResponse, Status>> + Send>>;
 
     async fn stream_dot_events(&self, request: Request<StreamDotEventsRequest>) -> TonicResult<Response<Self::StreamDotEventsStream>> {
-        use crate::services::streaming::{DotEventBroadcaster, dot_events::create_filter_from_request};
+        use crate::services::streaming::dot_events::{create_filter_from_request, resume_sequence};
 
         let req = request.into_inner();
         let subscriber_id = uuid::Uuid::new_v4().to_string();
@@ -1474,9 +1595,10 @@ impl VmService for VmServiceImpl {
 
         // Create filter from request
         let filter = create_filter_from_request(&req);
+        let from_sequence = resume_sequence(&req);
 
-        // Subscribe to events
-        let stream = broadcaster.subscribe(subscriber_id, filter).await;
+        // Subscribe to events, replaying from the event log when resuming
+        let stream = broadcaster.subscribe(subscriber_id, filter, from_sequence).await;
 
         let boxed_stream = Box::pin(stream);
         Ok(Response::new(boxed_stream))
@@ -16,5 +16,6 @@
 
 //! CLI tools for DotVM
 
+pub mod replay;
 pub mod run;
 pub mod transpile;
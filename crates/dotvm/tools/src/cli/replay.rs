@@ -0,0 +1,127 @@
+// Dotlanth
+// Copyright (C) 2025 Synerthink
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Replay command for re-running recorded dot executions
+
+use clap::Args;
+use dotdb_core::document::create_persistent_collection_manager;
+use dotvm_core::bytecode::BytecodeFile;
+use dotvm_core::vm::replay::{ReplayOutcome, ReplayReport, TraceStore, replay, trace_executor};
+use std::path::PathBuf;
+
+/// Namespace the runtime keeps execution traces under
+const TRACE_NAMESPACE: &str = "dotvm";
+
+/// Arguments for the replay command
+#[derive(Args, Debug)]
+pub struct ReplayArgs {
+    /// Id of the recorded execution trace
+    #[arg(value_name = "TRACE_ID")]
+    pub trace_id: String,
+
+    /// Document store the runtime recorded the trace into (its DOTDB_DOCUMENT_PATH)
+    #[arg(long, value_name = "DIR")]
+    pub documents: PathBuf,
+
+    /// Verbose output
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+/// Execute the replay command, failing when the replay diverges from the recording
+pub fn replay_trace(args: ReplayArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let documents = create_persistent_collection_manager(&args.documents, None)?.with_namespace(TRACE_NAMESPACE)?;
+    let store = TraceStore::open(documents)?;
+    let trace = store.get(&args.trace_id)?.ok_or_else(|| format!("Trace {} not found", args.trace_id))?;
+    let bytecode = store
+        .bytecode(&trace.bytecode_sha256)?
+        .ok_or_else(|| format!("Bytecode {} of trace {} not found", trace.bytecode_sha256, args.trace_id))?;
+
+    println!("Replaying trace {} of dot {} version {}", args.trace_id, trace.dot_id, trace.dot_version);
+    if let Some(state_version) = trace.state_version {
+        println!("State version: {state_version}");
+    }
+    if !trace.masked_inputs.is_empty() {
+        println!("Masked inputs: {}", trace.masked_inputs.join(", "));
+    }
+
+    let mut executor = trace_executor(&trace.dot_id);
+    executor.load_bytecode(BytecodeFile::load_from_bytes(&bytecode)?)?;
+    let report = replay(&mut executor, &trace);
+    print_report(&report, args.verbose);
+
+    match report.outcome {
+        ReplayOutcome::Diverged(divergence) => Err(format!("Replay {divergence}").into()),
+        ReplayOutcome::Matched | ReplayOutcome::Truncated { .. } => Ok(()),
+    }
+}
+
+fn print_report(report: &ReplayReport, verbose: bool) {
+    println!("Host calls replayed: {}", report.host_calls_replayed);
+    println!("Instructions executed: {}", report.instructions_executed);
+
+    match &report.outcome {
+        ReplayOutcome::Matched => println!("Replay matched the recorded execution"),
+        ReplayOutcome::Diverged(divergence) => println!("Replay {divergence}"),
+        ReplayOutcome::Truncated { offset: Some(offset) } => println!("Replay matched up to offset {offset:#06x}, where the trace was truncated"),
+        ReplayOutcome::Truncated { offset: None } => println!("Replay matched every recorded host call; the trace was truncated before its outputs"),
+    }
+
+    if verbose && !report.final_stack.is_empty() {
+        println!("Final stack contents:");
+        for (i, value) in report.final_stack.iter().enumerate() {
+            println!("  [{i}]: {value}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dotvm_core::bytecode::VmArchitecture;
+    use dotvm_core::opcode::stack_opcodes::StackOpcode;
+    use dotvm_core::vm::replay::{ExecutionTrace, record};
+    use dotvm_core::vm::stack::StackValue;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_replay_recorded_trace() {
+        let mut bytecode = BytecodeFile::new(VmArchitecture::Arch64);
+        bytecode.add_instruction(StackOpcode::PushInt8.as_u8(), &[42]);
+        let bytes = [bytecode.header.to_bytes().as_slice(), &bytecode.code].concat();
+
+        let mut executor = trace_executor("dot_replayed");
+        executor.load_bytecode(BytecodeFile::load_from_bytes(&bytes).unwrap()).unwrap();
+        let mut trace = ExecutionTrace::new("dot_replayed", 1, record(&mut executor, usize::MAX));
+
+        let temp_dir = tempdir().unwrap();
+        let trace_id = {
+            let store = TraceStore::open(create_persistent_collection_manager(temp_dir.path(), None).unwrap().with_namespace(TRACE_NAMESPACE).unwrap()).unwrap();
+            let trace_id = store.insert(&mut trace, &bytes).unwrap();
+            trace.final_stack = vec![StackValue::Int64(7)];
+            store.insert(&mut trace, &bytes).unwrap();
+            trace_id
+        };
+        let args = |trace_id: &str| ReplayArgs {
+            trace_id: trace_id.to_string(),
+            documents: temp_dir.path().to_path_buf(),
+            verbose: false,
+        };
+
+        assert!(replay_trace(args(&trace_id)).is_ok());
+        assert!(replay_trace(args(&trace.trace_id)).is_err());
+    }
+}
@@ -275,6 +275,7 @@ fn execute_step_mode(executor: &mut VmExecutor, verbose: bool) -> Result<dotvm_c
         final_stack,
         halted: true,
         pc,
+        database_calls: Default::default(),
     })
 }
 
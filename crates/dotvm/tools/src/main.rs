@@ -19,6 +19,7 @@
 //! Main entry point for the DotVM command-line interface.
 
 use clap::{Parser, Subcommand};
+use dotvm_tools::cli::replay::{ReplayArgs, replay_trace};
 use dotvm_tools::cli::run::{RunArgs, run_bytecode};
 use dotvm_tools::cli::transpile::TranspileArgs;
 
@@ -37,6 +38,8 @@ enum Commands {
     Transpile(TranspileArgs),
     /// Run DotVM bytecode
     Run(RunArgs),
+    /// Re-run a recorded dot execution and report where it diverges
+    Replay(ReplayArgs),
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -65,6 +68,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         Commands::Run(args) => {
             run_bytecode(args)?;
         }
+        Commands::Replay(args) => {
+            replay_trace(args)?;
+        }
     }
 
     Ok(())